@@ -6,7 +6,7 @@
 
 //! Build script for Rustux kernel
 //!
-//! This build script compiles architecture-specific C code
+//! This build script compiles architecture-specific C and assembly code
 //! that provides low-level operations as a bridge to Rust.
 
 use std::env;
@@ -16,13 +16,55 @@ fn main() {
     // Get the target architecture
     let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
 
-    // Only build C code for x86_64
     if target_arch == "x86_64" {
         build_x86_64_c();
         configure_linker_x86_64();
+    } else if target_arch == "riscv64" {
+        build_riscv64_asm();
+        configure_linker_riscv64();
     }
 }
 
+fn configure_linker_riscv64() {
+    // Linked at the address OpenSBI jumps to on QEMU virt
+    println!("cargo:rustc-link-arg=-Tsrc/kernel/arch/riscv64/kernel_virt.ld");
+    println!("cargo:rustc-link-arg=--no-relax");
+    println!("cargo:rerun-if-changed=src/kernel/arch/riscv64/kernel_virt.ld");
+}
+
+fn build_riscv64_asm() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+
+    // Boot entry, trap entry/exit and user mode entry
+    let asm_sources = vec![
+        "src/kernel/arch/riscv64/start.S",
+        "src/kernel/arch/riscv64/exceptions.S",
+        "src/kernel/arch/riscv64/uspace_entry.S",
+    ];
+
+    let mut cc_build = cc::Build::new();
+    for src in &asm_sources {
+        cc_build.file(manifest_dir.join(src));
+        println!("cargo:rerun-if-changed={}", src);
+    }
+
+    cc_build
+        .include(manifest_dir.join("src/kernel/include"))
+        .include(manifest_dir.join("src/kernel/arch/riscv64/include"))
+        .warnings(true)
+        .flag("-march=rv64gc")
+        .flag("-mabi=lp64d")
+        .flag("-mcmodel=medany")
+        .flag("-mno-relax")
+        .define("__RUSTUX_KERNEL__", None);
+
+    // --whole-archive keeps start.S, which nothing references by name
+    cc_build.link_lib_modifier("+whole-archive");
+    cc_build.compile("arch_riscv64");
+
+    println!("cargo:rerun-if-changed=src/kernel/arch/riscv64/include/arch/asm_macros.h");
+}
+
 fn configure_linker_x86_64() {
     // Use custom linker script for bare-metal kernel
     println!("cargo:rustc-link-arg=-Tsrc/kernel/kernel_minimal.ld");
@@ -57,6 +99,7 @@ fn build_x86_64_c() {
     // Assembly source files for x86_64
     let asm_sources = vec![
         "src/kernel/arch/amd64/multiboot_header.S",
+        "src/kernel/arch/amd64/multiboot_entry.S",
     ];
 
     // Compile C code
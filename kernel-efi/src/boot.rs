@@ -0,0 +1,67 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot the system
+//!
+//! The command line mode runs the real system rather than a shell inside
+//! the firmware: it starts the Rustux loader from the volume this image
+//! was started from, which boots the kernel per its `boot.cfg`. On the
+//! running system the `console` service draws the terminal on the same
+//! framebuffer.
+
+use alloc::vec::Vec;
+
+use uefi::boot::LoadImageSource;
+use uefi::proto::device_path::DevicePath;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::BootPolicy;
+
+use crate::disk::peek;
+
+/// The loader, on the same volume
+const LOADER_PATH: &str = "\\EFI\\BOOT\\BOOTX64.EFI";
+
+/// Length of the end node closing every device path
+const END_NODE_SIZE: usize = 4;
+
+/// Device path of the loader: the boot volume's path with a file path
+/// node in place of its end node
+fn loader_path() -> Result<Vec<u8>, &'static str> {
+    let image = peek::<LoadedImage>(uefi::boot::image_handle()).ok_or("no loaded image protocol")?;
+    let device = image.device().ok_or("boot volume not found")?;
+    let volume = peek::<DevicePath>(device).ok_or("boot volume not found")?;
+    let volume = volume.as_bytes();
+
+    let mut path = volume[..volume.len() - END_NODE_SIZE].to_vec();
+    let file: Vec<u16> = LOADER_PATH.encode_utf16().chain([0]).collect();
+    // Media device path, file path node
+    path.extend_from_slice(&[0x04, 0x04]);
+    path.extend_from_slice(&((4 + file.len() * 2) as u16).to_le_bytes());
+    for c in &file {
+        path.extend_from_slice(&c.to_le_bytes());
+    }
+    // End of the entire device path
+    path.extend_from_slice(&[0x7F, 0xFF, 0x04, 0x00]);
+    Ok(path)
+}
+
+/// Start the loader
+///
+/// Only returns if the loader can't be started or returns itself.
+pub fn start_loader() -> Result<(), &'static str> {
+    let path = loader_path()?;
+    let device_path = <&DevicePath>::try_from(path.as_slice()).map_err(|_| "bad loader path")?;
+
+    let loader = uefi::boot::load_image(
+        uefi::boot::image_handle(),
+        LoadImageSource::FromDevicePath {
+            device_path,
+            boot_policy: BootPolicy::ExactMatch,
+        },
+    )
+    .map_err(|_| "loader not found")?;
+    uefi::boot::start_image(loader).map_err(|_| "the loader failed")
+}
@@ -0,0 +1,192 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Install target disks
+//!
+//! Whole disks are found through the firmware's Block I/O protocol.
+//! Listing only peeks at each disk; the one chosen for the install is
+//! then opened exclusively, which disconnects any file system driver
+//! bound to it while it is rewritten.
+
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+use uefi::boot::{
+    AllocateType, MemoryType, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol, SearchType,
+};
+use uefi::proto::device_path::DevicePath;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::media::block::BlockIO;
+use uefi::Handle;
+
+/// Size of the bounce buffer writes go through
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Length of the end node closing every device path
+const END_NODE_SIZE: usize = 4;
+
+/// A disk that can be installed to
+#[derive(Clone, Copy)]
+pub struct DiskInfo {
+    pub handle: Handle,
+    pub block_size: usize,
+    pub blocks: u64,
+    pub removable: bool,
+}
+
+impl DiskInfo {
+    /// Capacity in bytes
+    pub fn size(&self) -> u64 {
+        self.blocks * self.block_size as u64
+    }
+}
+
+/// Open a protocol without taking it from its drivers
+pub fn peek<P: uefi::proto::ProtocolPointer + ?Sized>(handle: Handle) -> Option<ScopedProtocol<P>> {
+    unsafe {
+        uefi::boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: uefi::boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+        .ok()
+    }
+}
+
+/// Device path of the volume the installer was started from
+fn boot_device_path() -> Option<Vec<u8>> {
+    let image = peek::<LoadedImage>(uefi::boot::image_handle())?;
+    let path = peek::<DevicePath>(image.device()?)?;
+    Some(path.as_bytes().to_vec())
+}
+
+/// Writable whole disks of at least `min_size` bytes
+///
+/// The disk holding the installation media is left out, so the install
+/// cannot overwrite the files it is copying.
+pub fn enumerate(min_size: u64) -> Vec<DiskInfo> {
+    let handles = match uefi::boot::locate_handle_buffer(SearchType::from_proto::<BlockIO>()) {
+        Ok(handles) => handles,
+        Err(_) => return Vec::new(),
+    };
+    let boot_path = boot_device_path();
+
+    let mut disks = Vec::new();
+    for &handle in handles.iter() {
+        let io = match peek::<BlockIO>(handle) {
+            Some(io) => io,
+            None => continue,
+        };
+        let media = io.media();
+        if !media.is_media_present() || media.is_logical_partition() || media.is_read_only() {
+            continue;
+        }
+
+        let block_size = media.block_size() as usize;
+        if block_size != 512 && block_size != 4096 {
+            continue;
+        }
+
+        // The boot volume is a partition, so its path extends the disk's
+        if let (Some(boot_path), Some(path)) = (&boot_path, peek::<DevicePath>(handle)) {
+            let path = path.as_bytes();
+            let prefix = &path[..path.len().saturating_sub(END_NODE_SIZE)];
+            if boot_path.starts_with(prefix) {
+                continue;
+            }
+        }
+
+        let disk = DiskInfo {
+            handle,
+            block_size,
+            blocks: media.last_block() + 1,
+            removable: media.is_removable_media(),
+        };
+        if disk.size() >= min_size {
+            disks.push(disk);
+        }
+    }
+    disks
+}
+
+/// Page-aligned buffer, meeting any Block I/O alignment requirement
+struct IoBuffer {
+    ptr: NonNull<u8>,
+    pages: usize,
+}
+
+impl IoBuffer {
+    fn new(size: usize) -> Option<Self> {
+        let pages = size.div_ceil(4096);
+        let ptr = uefi::boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages).ok()?;
+        Some(Self { ptr, pages })
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.pages * 4096) }
+    }
+}
+
+impl Drop for IoBuffer {
+    fn drop(&mut self) {
+        let _ = unsafe { uefi::boot::free_pages(self.ptr, self.pages) };
+    }
+}
+
+/// A disk opened for the install
+pub struct Disk {
+    io: ScopedProtocol<BlockIO>,
+    media_id: u32,
+    pub block_size: usize,
+    pub blocks: u64,
+    buffer: IoBuffer,
+}
+
+impl Disk {
+    /// Take the disk over from its drivers
+    pub fn open(info: &DiskInfo) -> Result<Self, &'static str> {
+        let io = uefi::boot::open_protocol_exclusive::<BlockIO>(info.handle)
+            .map_err(|_| "the disk is in use")?;
+        let media_id = io.media().media_id();
+        let buffer = IoBuffer::new(BUFFER_SIZE).ok_or("out of memory")?;
+        Ok(Self {
+            io,
+            media_id,
+            block_size: info.block_size,
+            blocks: info.blocks,
+            buffer,
+        })
+    }
+
+    /// Write `data` starting at block `lba`
+    ///
+    /// A partial last block is padded with zeros.
+    pub fn write(&mut self, mut lba: u64, data: &[u8]) -> Result<(), &'static str> {
+        for chunk in data.chunks(BUFFER_SIZE) {
+            let len = chunk.len().next_multiple_of(self.block_size);
+            if lba + (len / self.block_size) as u64 > self.blocks {
+                return Err("write past the end of the disk");
+            }
+
+            let buffer = &mut self.buffer.as_mut_slice()[..len];
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            buffer[chunk.len()..].fill(0);
+            self.io
+                .write_blocks(self.media_id, lba, buffer)
+                .map_err(|_| "disk write failed")?;
+            lba += (len / self.block_size) as u64;
+        }
+        Ok(())
+    }
+
+    /// Make sure everything written has reached the disk
+    pub fn flush(&mut self) -> Result<(), &'static str> {
+        self.io.flush_blocks().map_err(|_| "disk flush failed")
+    }
+}
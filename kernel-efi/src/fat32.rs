@@ -0,0 +1,508 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! FAT32 formatter
+//!
+//! Just enough FAT32 to build an EFI system partition: format it, create
+//! directories and write whole files. Clusters are handed out in order,
+//! so file data goes straight to the disk; the FAT and the directories
+//! stay in memory until `finish` writes them.
+//!
+//! Names that are not upper case 8.3 get a long name entry and a `~1`
+//! style short alias.
+
+use alloc::vec::Vec;
+
+use crate::disk::Disk;
+
+/// Cluster size in bytes
+const CLUSTER_SIZE: usize = 4096;
+
+/// Sectors before the first FAT
+const RESERVED_SECTORS: u32 = 32;
+
+/// Copies of the FAT
+const FAT_COUNT: u32 = 2;
+
+/// Sector of the FSInfo structure
+const FSINFO_SECTOR: u32 = 1;
+
+/// Sector of the boot sector copy, followed by its FSInfo copy
+const BACKUP_BOOT_SECTOR: u32 = 6;
+
+/// Fewest clusters a FAT32 volume may have
+const MIN_CLUSTERS: u32 = 65525;
+
+/// First data cluster, which holds the root directory
+const ROOT_CLUSTER: u32 = 2;
+
+/// FAT entry ending a cluster chain
+const END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+
+/// Fixed disk media descriptor
+const MEDIA_FIXED: u8 = 0xF8;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+/// Size of a directory entry
+const ENTRY_SIZE: usize = 32;
+
+/// Name characters in one long name entry
+const LONG_NAME_CHARS: usize = 13;
+
+/// Directory created on the volume
+#[derive(Clone, Copy)]
+pub struct Dir(usize);
+
+impl Dir {
+    pub const ROOT: Dir = Dir(0);
+}
+
+struct DirData {
+    clusters: Vec<u32>,
+    entries: Vec<[u8; ENTRY_SIZE]>,
+    /// Short aliases handed out, for `~N` numbering
+    aliases: u32,
+}
+
+/// A FAT32 volume being built on a partition
+pub struct Fat32<'a> {
+    disk: &'a mut Disk,
+    first_lba: u64,
+    sectors: u32,
+    sectors_per_cluster: u32,
+    fat_sectors: u32,
+    fat: Vec<u32>,
+    next_cluster: u32,
+    dirs: Vec<DirData>,
+    label: [u8; 11],
+    volume_id: u32,
+    /// FAT date and time stamped on every entry
+    date: u16,
+    time: u16,
+}
+
+impl<'a> Fat32<'a> {
+    /// Start a volume on the `sectors` blocks from `first_lba`
+    pub fn format(
+        disk: &'a mut Disk,
+        first_lba: u64,
+        sectors: u64,
+        label: &str,
+        volume_id: u32,
+    ) -> Result<Self, &'static str> {
+        let sector_size = disk.block_size as u32;
+        let sectors = u32::try_from(sectors).map_err(|_| "partition too large for FAT32")?;
+        let sectors_per_cluster = CLUSTER_SIZE as u32 / sector_size;
+        let (fat_sectors, clusters) = geometry(sector_size, sectors)?;
+
+        let mut fat = alloc::vec![0u32; clusters as usize + 2];
+        fat[0] = 0x0FFF_FF00 | MEDIA_FIXED as u32;
+        fat[1] = END_OF_CHAIN;
+
+        let mut volume_label = [b' '; 11];
+        for (dst, src) in volume_label.iter_mut().zip(label.bytes()) {
+            *dst = src.to_ascii_uppercase();
+        }
+
+        let (date, time) = timestamp();
+        let mut volume = Self {
+            disk,
+            first_lba,
+            sectors,
+            sectors_per_cluster,
+            fat_sectors,
+            fat,
+            next_cluster: ROOT_CLUSTER,
+            dirs: Vec::new(),
+            label: volume_label,
+            volume_id,
+            date,
+            time,
+        };
+
+        let root = volume.allocate(1)?;
+        let mut label_entry = [0u8; ENTRY_SIZE];
+        label_entry[0..11].copy_from_slice(&volume_label);
+        label_entry[11] = ATTR_VOLUME_ID;
+        volume.dirs.push(DirData {
+            clusters: alloc::vec![root],
+            entries: alloc::vec![label_entry],
+            aliases: 0,
+        });
+        Ok(volume)
+    }
+
+    /// Chain `count` free clusters, returning the first
+    fn allocate(&mut self, count: u32) -> Result<u32, &'static str> {
+        let first = self.next_cluster;
+        let end = first + count;
+        if end as usize > self.fat.len() {
+            return Err("EFI system partition full");
+        }
+        for cluster in first..end {
+            self.fat[cluster as usize] = if cluster + 1 == end { END_OF_CHAIN } else { cluster + 1 };
+        }
+        self.next_cluster = end;
+        Ok(first)
+    }
+
+    /// First block of a data cluster
+    fn cluster_lba(&self, cluster: u32) -> u64 {
+        let data_start = RESERVED_SECTORS + FAT_COUNT * self.fat_sectors;
+        self.first_lba + (data_start + (cluster - ROOT_CLUSTER) * self.sectors_per_cluster) as u64
+    }
+
+    /// Create a directory in `parent`
+    pub fn mkdir(&mut self, parent: Dir, name: &str) -> Result<Dir, &'static str> {
+        let cluster = self.allocate(1)?;
+        self.add_entry(parent, name, ATTR_DIRECTORY, cluster, 0);
+
+        // ".." of a directory in the root points at cluster 0
+        let parent_cluster = match parent.0 {
+            0 => 0,
+            index => self.dirs[index].clusters[0],
+        };
+        let dot = self.short_entry(*b".          ", ATTR_DIRECTORY, cluster, 0);
+        let dotdot = self.short_entry(*b"..         ", ATTR_DIRECTORY, parent_cluster, 0);
+        self.dirs.push(DirData {
+            clusters: alloc::vec![cluster],
+            entries: alloc::vec![dot, dotdot],
+            aliases: 0,
+        });
+        Ok(Dir(self.dirs.len() - 1))
+    }
+
+    /// Write a file into `dir`
+    pub fn write_file(&mut self, dir: Dir, name: &str, data: &[u8]) -> Result<(), &'static str> {
+        let size = u32::try_from(data.len()).map_err(|_| "file too large for FAT32")?;
+        let cluster = match data.len().div_ceil(CLUSTER_SIZE) {
+            0 => 0,
+            count => {
+                let first = self.allocate(count as u32)?;
+                let lba = self.cluster_lba(first);
+                self.disk.write(lba, data)?;
+                first
+            }
+        };
+        self.add_entry(dir, name, ATTR_ARCHIVE, cluster, size);
+        Ok(())
+    }
+
+    fn short_entry(&self, name: [u8; 11], attributes: u8, cluster: u32, size: u32) -> [u8; ENTRY_SIZE] {
+        let mut entry = [0u8; ENTRY_SIZE];
+        entry[0..11].copy_from_slice(&name);
+        entry[11] = attributes;
+        entry[14..16].copy_from_slice(&self.time.to_le_bytes());
+        entry[16..18].copy_from_slice(&self.date.to_le_bytes());
+        entry[18..20].copy_from_slice(&self.date.to_le_bytes());
+        entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        entry[22..24].copy_from_slice(&self.time.to_le_bytes());
+        entry[24..26].copy_from_slice(&self.date.to_le_bytes());
+        entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+        entry[28..32].copy_from_slice(&size.to_le_bytes());
+        entry
+    }
+
+    /// Add an entry, with long name entries if the name needs them
+    fn add_entry(&mut self, dir: Dir, name: &str, attributes: u8, cluster: u32, size: u32) {
+        let short = match short_name(name) {
+            Some(short) => short,
+            None => {
+                let data = &mut self.dirs[dir.0];
+                data.aliases += 1;
+                let alias = short_alias(name, data.aliases);
+                data.entries.extend(long_name_entries(name, checksum(&alias)));
+                alias
+            }
+        };
+        let entry = self.short_entry(short, attributes, cluster, size);
+        self.dirs[dir.0].entries.push(entry);
+    }
+
+    /// Write the directories, FATs, FSInfo and boot sectors
+    ///
+    /// The boot sector goes last, so the volume only becomes valid once
+    /// everything it describes is in place.
+    pub fn finish(mut self) -> Result<(), &'static str> {
+        let per_cluster = CLUSTER_SIZE / ENTRY_SIZE;
+        for index in 0..self.dirs.len() {
+            let needed = self.dirs[index].entries.len().div_ceil(per_cluster) as u32;
+            let have = self.dirs[index].clusters.len() as u32;
+            if needed > have {
+                let first = self.allocate(needed - have)?;
+                let last = *self.dirs[index].clusters.last().unwrap();
+                self.fat[last as usize] = first;
+                self.dirs[index].clusters.extend(first..first + needed - have);
+            }
+
+            let dir = &self.dirs[index];
+            for (i, &cluster) in dir.clusters.iter().enumerate() {
+                let mut data = [0u8; CLUSTER_SIZE];
+                let entries = dir.entries.iter().skip(i * per_cluster).take(per_cluster);
+                for (slot, entry) in data.chunks_exact_mut(ENTRY_SIZE).zip(entries) {
+                    slot.copy_from_slice(entry);
+                }
+                let lba = self.cluster_lba(cluster);
+                self.disk.write(lba, &data)?;
+            }
+        }
+
+        // The whole FAT is written, clearing whatever was there before
+        let sector_size = self.disk.block_size;
+        let mut fat = alloc::vec![0u8; self.fat_sectors as usize * sector_size];
+        for (bytes, entry) in fat.chunks_exact_mut(4).zip(&self.fat) {
+            bytes.copy_from_slice(&entry.to_le_bytes());
+        }
+        for copy in 0..FAT_COUNT {
+            let lba = self.first_lba + (RESERVED_SECTORS + copy * self.fat_sectors) as u64;
+            self.disk.write(lba, &fat)?;
+        }
+
+        let boot = self.boot_sector();
+        let fsinfo = self.fsinfo();
+        for base in [BACKUP_BOOT_SECTOR, 0] {
+            self.disk.write(self.first_lba + (base + FSINFO_SECTOR) as u64, &fsinfo[..sector_size])?;
+            self.disk.write(self.first_lba + base as u64, &boot[..sector_size])?;
+        }
+        self.disk.flush()
+    }
+
+    fn boot_sector(&self) -> [u8; 4096] {
+        let mut sector = [0u8; 4096];
+        sector[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        sector[3..11].copy_from_slice(b"RUSTUX  ");
+        sector[11..13].copy_from_slice(&(self.disk.block_size as u16).to_le_bytes());
+        sector[13] = self.sectors_per_cluster as u8;
+        sector[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        sector[16] = FAT_COUNT as u8;
+        sector[21] = MEDIA_FIXED;
+        sector[24..26].copy_from_slice(&63u16.to_le_bytes());
+        sector[26..28].copy_from_slice(&255u16.to_le_bytes());
+        sector[28..32].copy_from_slice(&(self.first_lba.min(u32::MAX as u64) as u32).to_le_bytes());
+        sector[32..36].copy_from_slice(&self.sectors.to_le_bytes());
+        sector[36..40].copy_from_slice(&self.fat_sectors.to_le_bytes());
+        sector[44..48].copy_from_slice(&ROOT_CLUSTER.to_le_bytes());
+        sector[48..50].copy_from_slice(&(FSINFO_SECTOR as u16).to_le_bytes());
+        sector[50..52].copy_from_slice(&(BACKUP_BOOT_SECTOR as u16).to_le_bytes());
+        sector[64] = 0x80;
+        sector[66] = 0x29;
+        sector[67..71].copy_from_slice(&self.volume_id.to_le_bytes());
+        sector[71..82].copy_from_slice(&self.label);
+        sector[82..90].copy_from_slice(b"FAT32   ");
+        sector[510] = 0x55;
+        sector[511] = 0xAA;
+        sector
+    }
+
+    fn fsinfo(&self) -> [u8; 4096] {
+        let free = (self.fat.len() as u32).saturating_sub(self.next_cluster);
+        let mut sector = [0u8; 4096];
+        sector[0..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        sector[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        sector[488..492].copy_from_slice(&free.to_le_bytes());
+        sector[492..496].copy_from_slice(&self.next_cluster.to_le_bytes());
+        sector[508..512].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+        sector
+    }
+}
+
+/// FAT size in sectors and cluster count of a volume of `sectors`
+fn geometry(sector_size: u32, sectors: u32) -> Result<(u32, u32), &'static str> {
+    let sectors_per_cluster = CLUSTER_SIZE as u32 / sector_size;
+
+    // Every cluster costs a 4 byte entry in each FAT
+    let usable = sectors.checked_sub(RESERVED_SECTORS).ok_or("partition too small for FAT32")?;
+    let fat_sectors = usable.div_ceil(sector_size / 4 * sectors_per_cluster + FAT_COUNT);
+    let data_start = RESERVED_SECTORS + FAT_COUNT * fat_sectors;
+    let clusters = ((sectors - data_start) / sectors_per_cluster).min(fat_sectors * sector_size / 4 - 2);
+    if clusters < MIN_CLUSTERS {
+        return Err("partition too small for FAT32");
+    }
+    Ok((fat_sectors, clusters))
+}
+
+/// Long name entries for `name`, last part first
+fn long_name_entries(name: &str, checksum: u8) -> Vec<[u8; ENTRY_SIZE]> {
+    let chars: Vec<u16> = name.encode_utf16().collect();
+    let parts = chars.len().div_ceil(LONG_NAME_CHARS);
+
+    let mut entries = Vec::with_capacity(parts);
+    for part in (0..parts).rev() {
+        // The name ends with a NUL if it fits, then 0xFFFF padding
+        let mut field = [0xFFFFu16; LONG_NAME_CHARS];
+        for (i, slot) in field.iter_mut().enumerate() {
+            let index = part * LONG_NAME_CHARS + i;
+            if index < chars.len() {
+                *slot = chars[index];
+            } else if index == chars.len() {
+                *slot = 0;
+            }
+        }
+
+        let mut entry = [0u8; ENTRY_SIZE];
+        entry[0] = (part + 1) as u8 | if part + 1 == parts { 0x40 } else { 0 };
+        entry[11] = ATTR_LONG_NAME;
+        entry[13] = checksum;
+        let offsets = (1..11).step_by(2).chain((14..26).step_by(2)).chain((28..32).step_by(2));
+        for (offset, c) in offsets.zip(field) {
+            entry[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+/// `name` as an 8.3 name, if it is one in upper case
+fn short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    let valid = |part: &str, max: usize| {
+        part.len() <= max
+            && part
+                .bytes()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || b"_-~!#$%&".contains(&c))
+    };
+    if base.is_empty() || !valid(base, 8) || !valid(ext, 3) {
+        return None;
+    }
+
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short)
+}
+
+/// Short alias `BASE~N.EXT` for a long name
+fn short_alias(name: &str, n: u32) -> [u8; 11] {
+    let (base, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let keep = |c: &u8| c.is_ascii_alphanumeric();
+
+    let mut alias = [b' '; 11];
+    let suffix = alloc::format!("~{}", n);
+    let base_len = 8 - suffix.len();
+    let mut len = 0;
+    for c in base.bytes().filter(keep).take(base_len) {
+        alias[len] = c.to_ascii_uppercase();
+        len += 1;
+    }
+    alias[len..len + suffix.len()].copy_from_slice(suffix.as_bytes());
+    for (i, c) in ext.bytes().filter(keep).take(3).enumerate() {
+        alias[8 + i] = c.to_ascii_uppercase();
+    }
+    alias
+}
+
+/// Checksum tying long name entries to their short entry
+fn checksum(short: &[u8; 11]) -> u8 {
+    short.iter().fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Current time as a FAT date and time, or 1980-01-01 without a clock
+fn timestamp() -> (u16, u16) {
+    match uefi::runtime::get_time() {
+        Ok(t) if t.year() >= 1980 => {
+            let date = (t.year() - 1980) << 9 | (t.month() as u16) << 5 | t.day() as u16;
+            let time = (t.hour() as u16) << 11 | (t.minute() as u16) << 5 | (t.second() / 2) as u16;
+            (date, time)
+        }
+        _ => (1 << 5 | 1, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometry() {
+        // 512 MiB ESP with 512-byte sectors
+        assert_eq!(geometry(512, 1024 * 1024), Ok((1022, 130812)));
+        assert_eq!(geometry(4096, 128 * 1024), Ok((128, 130784)));
+
+        for (sector_size, sectors) in [(512, 1024 * 1024), (512, 3 * 1024 * 1024 + 7), (4096, 128 * 1024)] {
+            let (fat_sectors, clusters) = geometry(sector_size, sectors).unwrap();
+            let data_start = RESERVED_SECTORS + FAT_COUNT * fat_sectors;
+            // The data fits the partition and the FAT has an entry for
+            // every cluster, plus the two reserved ones
+            assert!(data_start + clusters * (CLUSTER_SIZE as u32 / sector_size) <= sectors);
+            assert!(fat_sectors * sector_size / 4 >= clusters + 2);
+        }
+    }
+
+    #[test]
+    fn test_geometry_too_small() {
+        // FAT32 needs 65525 clusters, some 256 MiB of 4 KiB clusters
+        assert_eq!(geometry(512, 128 * 1024).err(), Some("partition too small for FAT32"));
+        assert_eq!(geometry(512, 16).err(), Some("partition too small for FAT32"));
+    }
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(short_name("EFI"), Some(*b"EFI        "));
+        assert_eq!(short_name("BOOTX64.EFI"), Some(*b"BOOTX64 EFI"));
+        assert_eq!(short_name("A_1~.B$"), Some(*b"A_1~    B$ "));
+
+        // Lower case, too long, or not a name at all
+        assert_eq!(short_name("Rustux"), None);
+        assert_eq!(short_name("kernel.elf"), None);
+        assert_eq!(short_name("BOOTFS12.IMAG"), None);
+        assert_eq!(short_name("LONGNAMES.IMG"), None);
+        assert_eq!(short_name("A.B.C"), None);
+        assert_eq!(short_name(".EFI"), None);
+        assert_eq!(short_name("A B"), None);
+    }
+
+    #[test]
+    fn test_short_alias() {
+        assert_eq!(short_alias("Rustux", 1), *b"RUSTUX~1   ");
+        assert_eq!(short_alias("bootfs.img", 1), *b"BOOTFS~1IMG");
+        assert_eq!(short_alias("boot.cfg", 2), *b"BOOT~2  CFG");
+        assert_eq!(short_alias("kernel.elf.sig", 3), *b"KERNEL~3SIG");
+        assert_eq!(short_alias("a b+c.efi", 12), *b"ABC~12  EFI");
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(b"BOOTFS~1IMG"), 0x17);
+        assert_eq!(checksum(b"KERNEL~1ELF"), 0x9D);
+    }
+
+    fn name_chars(entry: &[u8; ENTRY_SIZE]) -> Vec<u16> {
+        (1..11)
+            .step_by(2)
+            .chain((14..26).step_by(2))
+            .chain((28..32).step_by(2))
+            .map(|offset| u16::from_le_bytes([entry[offset], entry[offset + 1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_long_name_entries() {
+        let entries = long_name_entries("kernel.elf", 0x9D);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0][0], 0x41);
+        assert_eq!(entries[0][11], ATTR_LONG_NAME);
+        assert_eq!(entries[0][13], 0x9D);
+        let mut expected: Vec<u16> = "kernel.elf".encode_utf16().collect();
+        expected.extend([0, 0xFFFF, 0xFFFF]);
+        assert_eq!(name_chars(&entries[0]), expected);
+
+        // Exactly one entry long: no NUL
+        let entries = long_name_entries("Rustux-system", 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(name_chars(&entries[0]), "Rustux-system".encode_utf16().collect::<Vec<_>>());
+
+        // Two entries, the last part first
+        let entries = long_name_entries("Rustux system image", 0);
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0][0], entries[1][0]), (0x42, 0x01));
+        assert_eq!(name_chars(&entries[1]), "Rustux system".encode_utf16().collect::<Vec<_>>());
+        assert_eq!(name_chars(&entries[0])[..7], [b' ', b'i', b'm', b'a', b'g', b'e', 0].map(u16::from));
+    }
+}
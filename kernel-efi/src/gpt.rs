@@ -0,0 +1,376 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! GUID partition table
+//!
+//! An install disk gets a fresh table with two partitions: the EFI system
+//! partition holding the loader, kernel and initrd, and the Rustux system
+//! partition taking the rest of the disk. Partitions start on 1 MiB
+//! boundaries. Block 0 holds a protective MBR so MBR-only tools see the
+//! disk as in use.
+
+use uefi::{guid, Guid};
+
+use crate::disk::Disk;
+
+/// EFI system partition type
+pub const ESP_TYPE: Guid = guid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b");
+
+/// Rustux system partition type, holding the system image
+pub const SYSTEM_TYPE: Guid = guid!("3b8f9e12-5a6c-4d07-b1e4-7f2c9a0d6e53");
+
+/// Partition alignment in bytes
+const ALIGNMENT: u64 = 1024 * 1024;
+
+/// Entries in each partition array, the minimum the spec allows
+const ENTRY_COUNT: usize = 128;
+
+/// Size of one partition entry
+const ENTRY_SIZE: usize = 128;
+
+/// Size of the header, without its block padding
+const HEADER_SIZE: usize = 92;
+
+/// `"EFI PART"`
+const HEADER_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// Header revision 1.0
+const HEADER_REVISION: u32 = 0x0001_0000;
+
+/// MBR partition type covering a GPT disk
+const PROTECTIVE_MBR_TYPE: u8 = 0xEE;
+
+/// One partition of the new table
+#[derive(Clone, Copy)]
+pub struct Partition {
+    pub type_guid: Guid,
+    pub guid: Guid,
+    pub first_lba: u64,
+    pub last_lba: u64,
+    pub name: &'static str,
+}
+
+impl Partition {
+    pub fn blocks(&self) -> u64 {
+        self.last_lba - self.first_lba + 1
+    }
+}
+
+/// Partition table of an install disk
+pub struct Layout {
+    pub disk_guid: Guid,
+    pub esp: Partition,
+    pub system: Partition,
+    block_size: usize,
+    blocks: u64,
+}
+
+impl Layout {
+    /// Lay out a disk with an ESP of `esp_size` bytes
+    ///
+    /// Fails if the system partition would be smaller than `system_size`.
+    pub fn new(block_size: usize, blocks: u64, esp_size: u64, system_size: u64) -> Result<Self, &'static str> {
+        let guids = [random_guid(), random_guid(), random_guid()];
+        Self::with_guids(block_size, blocks, esp_size, system_size, guids)
+    }
+
+    /// Lay out a disk with the given disk, ESP and system partition GUIDs
+    pub fn with_guids(
+        block_size: usize,
+        blocks: u64,
+        esp_size: u64,
+        system_size: u64,
+        [disk_guid, esp_guid, system_guid]: [Guid; 3],
+    ) -> Result<Self, &'static str> {
+        let block_size_64 = block_size as u64;
+        let align = ALIGNMENT / block_size_64;
+        let array_blocks = (ENTRY_COUNT * ENTRY_SIZE / block_size) as u64;
+
+        // Protective MBR and header, then the array; the backup array and
+        // header close the disk
+        let first_usable = 2 + array_blocks;
+        let last_usable = blocks
+            .checked_sub(2 + array_blocks)
+            .ok_or("disk too small")?;
+
+        let esp_first = first_usable.next_multiple_of(align);
+        let esp_last = esp_first + esp_size / block_size_64 - 1;
+        let system_first = (esp_last + 1).next_multiple_of(align);
+        if system_first > last_usable
+            || (last_usable - system_first + 1) * block_size_64 < system_size
+        {
+            return Err("disk too small");
+        }
+
+        Ok(Self {
+            disk_guid,
+            esp: Partition {
+                type_guid: ESP_TYPE,
+                guid: esp_guid,
+                first_lba: esp_first,
+                last_lba: esp_last,
+                name: "EFI system partition",
+            },
+            system: Partition {
+                type_guid: SYSTEM_TYPE,
+                guid: system_guid,
+                first_lba: system_first,
+                last_lba: last_usable,
+                name: "Rustux system",
+            },
+            block_size,
+            blocks,
+        })
+    }
+
+    fn array_blocks(&self) -> u64 {
+        (ENTRY_COUNT * ENTRY_SIZE / self.block_size) as u64
+    }
+
+    /// Partition number, from 1, of each partition in the table
+    pub fn number(&self, partition: &Partition) -> u32 {
+        if partition.guid == self.esp.guid { 1 } else { 2 }
+    }
+
+    /// The partition entry array
+    fn entries(&self) -> [u8; ENTRY_COUNT * ENTRY_SIZE] {
+        let mut array = [0u8; ENTRY_COUNT * ENTRY_SIZE];
+        for (entry, partition) in array.chunks_exact_mut(ENTRY_SIZE).zip([&self.esp, &self.system]) {
+            entry[0..16].copy_from_slice(&partition.type_guid.to_bytes());
+            entry[16..32].copy_from_slice(&partition.guid.to_bytes());
+            entry[32..40].copy_from_slice(&partition.first_lba.to_le_bytes());
+            entry[40..48].copy_from_slice(&partition.last_lba.to_le_bytes());
+            for (i, c) in partition.name.encode_utf16().take(36).enumerate() {
+                entry[56 + 2 * i..58 + 2 * i].copy_from_slice(&c.to_le_bytes());
+            }
+        }
+        array
+    }
+
+    /// A header, primary or backup, padded to a block
+    fn header(&self, primary: bool, entries_crc: u32) -> [u8; 4096] {
+        let backup_lba = self.blocks - 1;
+        let (my_lba, alternate_lba, array_lba) = if primary {
+            (1, backup_lba, 2)
+        } else {
+            (backup_lba, 1, backup_lba - self.array_blocks())
+        };
+
+        let mut header = [0u8; 4096];
+        header[0..8].copy_from_slice(HEADER_SIGNATURE);
+        header[8..12].copy_from_slice(&HEADER_REVISION.to_le_bytes());
+        header[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&my_lba.to_le_bytes());
+        header[32..40].copy_from_slice(&alternate_lba.to_le_bytes());
+        header[40..48].copy_from_slice(&(2 + self.array_blocks()).to_le_bytes());
+        header[48..56].copy_from_slice(&(self.blocks - 2 - self.array_blocks()).to_le_bytes());
+        header[56..72].copy_from_slice(&self.disk_guid.to_bytes());
+        header[72..80].copy_from_slice(&array_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&(ENTRY_COUNT as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&(ENTRY_SIZE as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        let crc = crc32(&header[..HEADER_SIZE]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        header
+    }
+
+    /// MBR with one partition covering the whole disk
+    fn protective_mbr(&self) -> [u8; 512] {
+        let mut mbr = [0u8; 512];
+        let entry = &mut mbr[446..462];
+        entry[1..4].copy_from_slice(&[0x00, 0x02, 0x00]);
+        entry[4] = PROTECTIVE_MBR_TYPE;
+        entry[5..8].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+        entry[8..12].copy_from_slice(&1u32.to_le_bytes());
+        let size = (self.blocks - 1).min(u32::MAX as u64) as u32;
+        entry[12..16].copy_from_slice(&size.to_le_bytes());
+        mbr[510] = 0x55;
+        mbr[511] = 0xAA;
+        mbr
+    }
+
+    /// Write both copies of the table
+    ///
+    /// The backup goes first: until the primary header is written the
+    /// disk keeps whatever table it had.
+    pub fn write(&self, disk: &mut Disk) -> Result<(), &'static str> {
+        let entries = self.entries();
+        let entries_crc = crc32(&entries);
+        let block = self.block_size;
+        let backup_lba = self.blocks - 1;
+
+        disk.write(backup_lba - self.array_blocks(), &entries)?;
+        disk.write(backup_lba, &self.header(false, entries_crc)[..block])?;
+        disk.write(2, &entries)?;
+        disk.write(1, &self.header(true, entries_crc)[..block])?;
+        disk.write(0, &self.protective_mbr())?;
+        disk.flush()
+    }
+}
+
+/// CRC-32 (IEEE 802.3), as the headers use
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Fill `buf` from the firmware RNG, falling back to TSC jitter
+pub fn random_bytes(buf: &mut [u8]) {
+    use uefi::proto::rng::Rng;
+
+    let firmware = uefi::boot::get_handle_for_protocol::<Rng>()
+        .and_then(uefi::boot::open_protocol_exclusive::<Rng>)
+        .and_then(|mut rng| rng.get_rng(None, buf));
+    if firmware.is_ok() {
+        return;
+    }
+
+    let mut value = 0u64;
+    for byte in buf.iter_mut() {
+        for _ in 0..8 {
+            let start = unsafe { core::arch::x86_64::_rdtsc() };
+            for _ in 0..(value & 0x1f) + 1 {
+                core::hint::spin_loop();
+            }
+            let delta = unsafe { core::arch::x86_64::_rdtsc() }.wrapping_sub(start);
+            value = value.rotate_left(7) ^ delta ^ start;
+        }
+        *byte = (value ^ value >> 32) as u8;
+    }
+}
+
+/// A random (version 4) GUID
+pub fn random_guid() -> Guid {
+    let mut bytes = [0u8; 16];
+    random_bytes(&mut bytes);
+    // Version in the top nibble of the little-endian third field, variant
+    // in the top bits of the fourth
+    bytes[7] = (bytes[7] & 0x0F) | 0x40;
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+    Guid::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn layout_of(block_size: usize, size: u64) -> Result<Layout, &'static str> {
+        let guids = [
+            guid!("11111111-2222-4333-8444-555555555555"),
+            guid!("aaaaaaaa-bbbb-4ccc-8ddd-eeeeeeeeeeee"),
+            guid!("01234567-89ab-4cde-8f01-23456789abcd"),
+        ];
+        Layout::with_guids(block_size, size / block_size as u64, 512 * 1024 * 1024, GIB, guids)
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_layout() {
+        // 512-byte blocks: the array takes 32 blocks, partitions on 2048
+        let layout = layout_of(512, 4 * GIB).unwrap();
+        assert_eq!(layout.esp.first_lba, 2048);
+        assert_eq!(layout.esp.blocks(), 1024 * 1024);
+        assert_eq!(layout.system.first_lba, 2048 + 1024 * 1024);
+        assert_eq!(layout.system.last_lba, 8 * 1024 * 1024 - 34);
+        assert_eq!(layout.number(&layout.esp), 1);
+        assert_eq!(layout.number(&layout.system), 2);
+
+        // 4096-byte blocks: the array takes 4 blocks, partitions on 256
+        let layout = layout_of(4096, 4 * GIB).unwrap();
+        assert_eq!(layout.esp.first_lba, 256);
+        assert_eq!(layout.esp.blocks(), 128 * 1024);
+        assert_eq!(layout.system.first_lba, 256 + 128 * 1024);
+        assert_eq!(layout.system.last_lba, 1024 * 1024 - 6);
+    }
+
+    #[test]
+    fn test_layout_too_small() {
+        // The system partition must hold the 1 GiB image
+        assert!(layout_of(512, 3 * GIB / 2 + 2 * 1024 * 1024).is_ok());
+        assert_eq!(layout_of(512, 3 * GIB / 2).err(), Some("disk too small"));
+        assert_eq!(layout_of(512, 512 * 1024 * 1024).err(), Some("disk too small"));
+        assert_eq!(layout_of(512, 16 * 1024).err(), Some("disk too small"));
+    }
+
+    #[test]
+    fn test_entries() {
+        let layout = layout_of(512, 4 * GIB).unwrap();
+        let entries = layout.entries();
+
+        let esp = &entries[..ENTRY_SIZE];
+        assert_eq!(esp[0..16], ESP_TYPE.to_bytes());
+        assert_eq!(esp[16..32], layout.esp.guid.to_bytes());
+        assert_eq!(u64_at(esp, 32), layout.esp.first_lba);
+        assert_eq!(u64_at(esp, 40), layout.esp.last_lba);
+        assert_eq!(esp[56..60], [b'E', 0, b'F', 0]);
+
+        let system = &entries[ENTRY_SIZE..2 * ENTRY_SIZE];
+        assert_eq!(system[0..16], SYSTEM_TYPE.to_bytes());
+        assert_eq!(u64_at(system, 40), layout.system.last_lba);
+        assert!(entries[2 * ENTRY_SIZE..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_headers() {
+        let layout = layout_of(512, 4 * GIB).unwrap();
+        let blocks = 8 * 1024 * 1024;
+        let entries_crc = crc32(&layout.entries());
+
+        for (primary, my_lba, alternate_lba, array_lba) in
+            [(true, 1, blocks - 1, 2), (false, blocks - 1, 1, blocks - 33)]
+        {
+            let header = layout.header(primary, entries_crc);
+            assert_eq!(&header[0..8], HEADER_SIGNATURE);
+            assert_eq!(u64_at(&header, 24), my_lba);
+            assert_eq!(u64_at(&header, 32), alternate_lba);
+            assert_eq!(u64_at(&header, 40), 34);
+            assert_eq!(u64_at(&header, 48), blocks - 34);
+            assert_eq!(header[56..72], layout.disk_guid.to_bytes());
+            assert_eq!(u64_at(&header, 72), array_lba);
+            assert_eq!(u32_at(&header, 88), entries_crc);
+
+            // The CRC covers the header with its own field zeroed
+            let mut unsigned = header;
+            unsigned[16..20].fill(0);
+            assert_eq!(u32_at(&header, 16), crc32(&unsigned[..HEADER_SIZE]));
+            assert!(header[HEADER_SIZE..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn test_protective_mbr() {
+        let mbr = layout_of(512, 4 * GIB).unwrap().protective_mbr();
+        assert_eq!(mbr[446 + 4], PROTECTIVE_MBR_TYPE);
+        assert_eq!(u32_at(&mbr, 446 + 8), 1);
+        assert_eq!(u32_at(&mbr, 446 + 12), 8 * 1024 * 1024 - 1);
+        assert_eq!(mbr[510..], [0x55, 0xAA]);
+
+        // Disks past 2 TiB are covered as far as the MBR can say
+        let big = Layout::with_guids(512, 1 << 40, 512 * 1024 * 1024, GIB, [Guid::ZERO; 3]).unwrap();
+        assert_eq!(u32_at(&big.protective_mbr(), 446 + 12), u32::MAX);
+    }
+}
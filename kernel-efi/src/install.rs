@@ -0,0 +1,508 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Install to disk
+//!
+//! Copies Rustux from the installation media, the volume this image was
+//! started from, to a disk the user picks:
+//!
+//! ```text
+//! \EFI\BOOT\BOOTX64.EFI        UEFI loader
+//! \EFI\Rustux\kernel.elf       kernel
+//! \EFI\Rustux\bootfs.img       initrd, optional
+//! \Rustux\desktop.img          system image for a desktop install
+//! \Rustux\server.img           system image for a server install
+//! ```
+//!
+//! Signatures (`<image>.sig`) next to the loader's images are copied with
+//! them. Every file is read before the disk is touched, so missing media
+//! files fail the install without erasing anything.
+//!
+//! The disk gets a new GPT with a FAT32 EFI system partition holding the
+//! loader, kernel, initrd and a `boot.cfg` booting them, and a Rustux
+//! system partition the system image is written to block for block. A
+//! `Boot####` load option for the loader is added in front of `BootOrder`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::time::Duration;
+
+use uefi::proto::console::text::{Color, Key, ScanCode};
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileInfo, FileMode, FileType, RegularFile};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::runtime::{ResetType, VariableAttributes, VariableVendor};
+use uefi::{cstr16, CString16, Status};
+
+use crate::disk::{self, Disk, DiskInfo};
+use crate::fat32::{self, Fat32};
+use crate::gpt::{self, Layout, Partition};
+use crate::InstallMode;
+
+/// Size of the EFI system partition
+const ESP_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Largest loader, kernel or initrd copied
+const BOOT_FILE_MAX: usize = 256 * 1024 * 1024;
+
+/// System image bytes copied per step
+const COPY_CHUNK: usize = 1024 * 1024;
+
+/// Key polling interval
+const POLL_INTERVAL_MS: u64 = 100;
+
+/// Loader path, on the media and on the installed ESP
+const LOADER_PATH: &str = "\\EFI\\BOOT\\BOOTX64.EFI";
+
+/// Suffix of detached signatures
+const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Title of the installed system, in boot.cfg and the firmware boot menu
+const SYSTEM_TITLE: &str = "Rustux OS";
+
+/// `LOAD_OPTION_ACTIVE`
+const LOAD_OPTION_ACTIVE: u32 = 0x0000_0001;
+
+/// A file for the ESP: media path, then directory and name on the ESP
+struct BootFile {
+    source: &'static str,
+    dir: &'static str,
+    name: &'static str,
+    required: bool,
+}
+
+const BOOT_FILES: [BootFile; 3] = [
+    BootFile { source: LOADER_PATH, dir: "BOOT", name: "BOOTX64.EFI", required: true },
+    BootFile { source: "\\EFI\\Rustux\\kernel.elf", dir: "Rustux", name: "kernel.elf", required: true },
+    BootFile { source: "\\EFI\\Rustux\\bootfs.img", dir: "Rustux", name: "bootfs.img", required: false },
+];
+
+/// Everything copied from the media
+struct Media {
+    /// ESP directory, name and contents of each file found
+    files: Vec<(&'static str, String, Vec<u8>)>,
+    image: RegularFile,
+    image_size: u64,
+}
+
+/// Print to the console
+fn print(args: core::fmt::Arguments) {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.write_fmt(args);
+    });
+}
+
+/// Wait for a key
+fn wait_key() -> Key {
+    loop {
+        if let Some(key) = uefi::system::with_stdin(|stdin| stdin.read_key().ok().flatten()) {
+            return key;
+        }
+        uefi::boot::stall(Duration::from_millis(POLL_INTERVAL_MS));
+    }
+}
+
+/// Human readable size
+fn size_string(bytes: u64) -> String {
+    const GIB: u64 = 1024 * 1024 * 1024;
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= GIB {
+        alloc::format!("{}.{} GiB", bytes / GIB, bytes % GIB * 10 / GIB)
+    } else {
+        alloc::format!("{} MiB", bytes / MIB)
+    }
+}
+
+/// Run the installer
+///
+/// Returns if the user backs out before the disk is touched; otherwise
+/// ends with a prompt to reboot.
+pub fn run(mode: InstallMode) {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.set_color(Color::White, Color::Black);
+        let _ = stdout.clear();
+    });
+    print(format_args!("Rustux OS Installer\r\n\r\n"));
+
+    let disks = disk::enumerate(ESP_SIZE * 2);
+    if disks.is_empty() {
+        print(format_args!("No disk to install to was found. Press any key to continue.\r\n"));
+        wait_key();
+        return;
+    }
+
+    let target = match select_disk(&disks) {
+        Some(target) => target,
+        None => return,
+    };
+
+    print(format_args!(
+        "\r\nAll data on disk {} will be erased. Press Y to install, any other key to cancel.\r\n",
+        target + 1
+    ));
+    match wait_key() {
+        Key::Printable(c) if matches!(char::from(c), 'y' | 'Y') => {}
+        _ => return,
+    }
+
+    print(format_args!("\r\n"));
+    match install(mode, &disks[target]) {
+        Ok(()) => {
+            uefi::system::with_stdout(|stdout| {
+                let _ = stdout.set_color(Color::LightGreen, Color::Black);
+            });
+            print(format_args!(
+                "\r\nInstallation complete.\r\nRemove the installation media and press any key to reboot.\r\n"
+            ));
+        }
+        Err(e) => {
+            uefi::system::with_stdout(|stdout| {
+                let _ = stdout.set_color(Color::LightRed, Color::Black);
+            });
+            print(format_args!("\r\nInstallation failed: {}\r\nPress any key to reboot.\r\n", e));
+        }
+    }
+
+    wait_key();
+    uefi::runtime::reset(ResetType::COLD, Status::SUCCESS, None);
+}
+
+/// Let the user pick a disk, or None on Escape
+fn select_disk(disks: &[DiskInfo]) -> Option<usize> {
+    print(format_args!("Select the disk to install to:\r\n\r\n"));
+    for (i, disk) in disks.iter().enumerate().take(9) {
+        print(format_args!(
+            "  [{}] Disk {}: {}, {}, {}-byte blocks\r\n",
+            i + 1,
+            i + 1,
+            size_string(disk.size()),
+            if disk.removable { "removable" } else { "fixed" },
+            disk.block_size
+        ));
+    }
+    print(format_args!("\r\nPress 1-{} to select, Esc to cancel.\r\n", disks.len().min(9)));
+
+    loop {
+        match wait_key() {
+            Key::Special(ScanCode::ESCAPE) => return None,
+            Key::Printable(c) => {
+                if let Some(index) = char::from(c).to_digit(10).and_then(|d| (d as usize).checked_sub(1)) {
+                    if index < disks.len().min(9) {
+                        return Some(index);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Install to `target`
+fn install(mode: InstallMode, target: &DiskInfo) -> Result<(), &'static str> {
+    print(format_args!("[1/5] Reading installation media\r\n"));
+    let mut media = read_media(mode)?;
+
+    print(format_args!("[2/5] Partitioning disk\r\n"));
+    let mut disk = Disk::open(target)?;
+    let layout = Layout::new(disk.block_size, disk.blocks, ESP_SIZE, media.image_size)?;
+    layout.write(&mut disk)?;
+
+    print(format_args!("[3/5] Writing EFI system partition\r\n"));
+    write_esp(&mut disk, &layout, &media)?;
+
+    print(format_args!("[4/5] Copying system image\r\n"));
+    copy_image(&mut disk, &layout.system, &mut media)?;
+
+    print(format_args!("[5/5] Adding boot entry\r\n"));
+    add_boot_option(&layout)
+}
+
+/// Open the volume the installer was started from
+fn open_media() -> Result<Directory, &'static str> {
+    let image = uefi::boot::open_protocol_exclusive::<LoadedImage>(uefi::boot::image_handle())
+        .map_err(|_| "no loaded image protocol")?;
+    let device = image.device().ok_or("installation media not found")?;
+    let mut fs = uefi::boot::open_protocol_exclusive::<SimpleFileSystem>(device)
+        .map_err(|_| "installation media not readable")?;
+    fs.open_volume().map_err(|_| "installation media not readable")
+}
+
+/// Open a regular file on the media, with its size
+fn open_file(root: &mut Directory, path: &str) -> Option<(RegularFile, u64)> {
+    let path = CString16::try_from(path).ok()?;
+    let handle = root.open(&path, FileMode::Read, FileAttribute::empty()).ok()?;
+    let mut file = match handle.into_type().ok()? {
+        FileType::Regular(file) => file,
+        FileType::Dir(_) => return None,
+    };
+
+    let mut info_buf = [0u8; 256];
+    let size = file.get_info::<FileInfo>(&mut info_buf).ok()?.file_size();
+    Some((file, size))
+}
+
+/// Read a whole file of at most `max` bytes from the media
+fn read_file(root: &mut Directory, path: &str, max: usize) -> Option<Vec<u8>> {
+    let (mut file, size) = open_file(root, path)?;
+    if size > max as u64 {
+        return None;
+    }
+
+    let mut data = alloc::vec![0u8; size as usize];
+    let read = file.read(&mut data).ok()?;
+    data.truncate(read);
+    Some(data)
+}
+
+/// Read the boot files and open the system image
+fn read_media(mode: InstallMode) -> Result<Media, &'static str> {
+    let mut root = open_media()?;
+
+    let mut files = Vec::new();
+    for file in &BOOT_FILES {
+        let data = match read_file(&mut root, file.source, BOOT_FILE_MAX) {
+            Some(data) => data,
+            None if file.required => return Err("boot file missing from the installation media"),
+            None => continue,
+        };
+        files.push((file.dir, String::from(file.name), data));
+
+        let signature = alloc::format!("{}{}", file.source, SIGNATURE_SUFFIX);
+        if let Some(data) = read_file(&mut root, &signature, 4096) {
+            files.push((file.dir, alloc::format!("{}{}", file.name, SIGNATURE_SUFFIX), data));
+        }
+    }
+
+    let image_path = match mode {
+        InstallMode::Desktop => "\\Rustux\\desktop.img",
+        InstallMode::Server => "\\Rustux\\server.img",
+    };
+    let (image, image_size) =
+        open_file(&mut root, image_path).ok_or("system image missing from the installation media")?;
+
+    Ok(Media { files, image, image_size })
+}
+
+/// Format the ESP and copy the boot files to it
+fn write_esp(disk: &mut Disk, layout: &Layout, media: &Media) -> Result<(), &'static str> {
+    let mut volume_id = [0u8; 4];
+    gpt::random_bytes(&mut volume_id);
+
+    let esp = &layout.esp;
+    let mut volume = Fat32::format(disk, esp.first_lba, esp.blocks(), "RUSTUX", u32::from_le_bytes(volume_id))?;
+    let efi = volume.mkdir(fat32::Dir::ROOT, "EFI")?;
+    let boot = volume.mkdir(efi, "BOOT")?;
+    let rustux = volume.mkdir(efi, "Rustux")?;
+
+    for (dir, name, data) in &media.files {
+        print(format_args!("      {}\r\n", name));
+        let dir = if *dir == "BOOT" { boot } else { rustux };
+        volume.write_file(dir, name, data)?;
+    }
+
+    let config = boot_config(media.files.iter().any(|(_, name, _)| name == "bootfs.img"));
+    print(format_args!("      boot.cfg\r\n"));
+    volume.write_file(rustux, "boot.cfg", config.as_bytes())?;
+
+    volume.finish()
+}
+
+/// `boot.cfg` booting the installed kernel, and the initrd if there is one
+fn boot_config(initrd: bool) -> String {
+    let mut config = String::new();
+    let _ = write!(
+        config,
+        "# Written by the Rustux installer\ntimeout 3\ndefault {title}\n\nentry {title}\n    kernel \\EFI\\Rustux\\kernel.elf\n",
+        title = SYSTEM_TITLE
+    );
+    if initrd {
+        config.push_str("    initrd \\EFI\\Rustux\\bootfs.img\n");
+    }
+    config
+}
+
+/// Copy the system image to the start of the system partition
+fn copy_image(disk: &mut Disk, system: &Partition, media: &mut Media) -> Result<(), &'static str> {
+    let mut chunk = alloc::vec![0u8; COPY_CHUNK];
+    let block_size = disk.block_size as u64;
+    let mut copied = 0u64;
+    let mut shown = None;
+
+    while copied < media.image_size {
+        let read = media.image.read(&mut chunk).map_err(|_| "installation media read failed")?;
+        if read == 0 {
+            return Err("system image truncated");
+        }
+        disk.write(system.first_lba + copied / block_size, &chunk[..read])?;
+        copied += read as u64;
+
+        // A short read leaves a partial block, which the next write would
+        // not line up with
+        if copied % block_size != 0 && copied < media.image_size {
+            return Err("installation media read failed");
+        }
+
+        let percent = copied * 100 / media.image_size;
+        if shown != Some(percent) {
+            print(format_args!("\r      {:3}%  {} of {}", percent, size_string(copied), size_string(media.image_size)));
+            shown = Some(percent);
+        }
+    }
+    print(format_args!("\r\n"));
+    disk.flush()
+}
+
+/// Add a load option for the installed loader and boot it first
+fn add_boot_option(layout: &Layout) -> Result<(), &'static str> {
+    let vendor = VariableVendor::GLOBAL_VARIABLE;
+    let attributes =
+        VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS | VariableAttributes::RUNTIME_ACCESS;
+
+    let mut order_buf = [0u8; 512];
+    let order: Vec<u16> = match uefi::runtime::get_variable(cstr16!("BootOrder"), &vendor, &mut order_buf) {
+        Ok((data, _)) => data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect(),
+        Err(_) => Vec::new(),
+    };
+
+    // First unused option number
+    let number = (0..=u16::MAX)
+        .find(|&n| {
+            let mut probe = [0u8; 1];
+            match uefi::runtime::get_variable(&boot_option_name(n), &vendor, &mut probe) {
+                Err(e) => e.status() == Status::NOT_FOUND,
+                Ok(_) => false,
+            }
+        })
+        .ok_or("no free boot option")?;
+
+    uefi::runtime::set_variable(&boot_option_name(number), &vendor, attributes, &load_option(layout))
+        .map_err(|_| "the firmware refused the boot option")?;
+
+    uefi::runtime::set_variable(cstr16!("BootOrder"), &vendor, attributes, &boot_order(number, &order))
+        .map_err(|_| "the firmware refused the boot order")
+}
+
+/// `BootOrder` with option `number` moved or added to the front
+fn boot_order(number: u16, order: &[u16]) -> Vec<u8> {
+    let mut new_order = Vec::with_capacity(order.len() * 2 + 2);
+    new_order.extend_from_slice(&number.to_le_bytes());
+    for &n in order.iter().filter(|&&n| n != number) {
+        new_order.extend_from_slice(&n.to_le_bytes());
+    }
+    new_order
+}
+
+/// `Boot####` variable name
+fn boot_option_name(number: u16) -> CString16 {
+    CString16::try_from(alloc::format!("Boot{:04X}", number).as_str()).unwrap()
+}
+
+/// `EFI_LOAD_OPTION` starting the loader from the ESP
+///
+/// The device path names the ESP by its GPT partition, so it stays valid
+/// wherever the firmware enumerates the disk.
+fn load_option(layout: &Layout) -> Vec<u8> {
+    let esp = &layout.esp;
+
+    let mut path = Vec::new();
+    // Media device path, hard drive node
+    path.extend_from_slice(&[0x04, 0x01]);
+    path.extend_from_slice(&42u16.to_le_bytes());
+    path.extend_from_slice(&layout.number(esp).to_le_bytes());
+    path.extend_from_slice(&esp.first_lba.to_le_bytes());
+    path.extend_from_slice(&esp.blocks().to_le_bytes());
+    path.extend_from_slice(&esp.guid.to_bytes());
+    // GPT partition format, GUID signature
+    path.extend_from_slice(&[0x02, 0x02]);
+
+    // Media device path, file path node
+    let file: Vec<u16> = LOADER_PATH.encode_utf16().chain([0]).collect();
+    path.extend_from_slice(&[0x04, 0x04]);
+    path.extend_from_slice(&((4 + file.len() * 2) as u16).to_le_bytes());
+    for c in &file {
+        path.extend_from_slice(&c.to_le_bytes());
+    }
+
+    // End of the entire device path
+    path.extend_from_slice(&[0x7F, 0xFF, 0x04, 0x00]);
+
+    let mut option = Vec::new();
+    option.extend_from_slice(&LOAD_OPTION_ACTIVE.to_le_bytes());
+    option.extend_from_slice(&(path.len() as u16).to_le_bytes());
+    for c in SYSTEM_TITLE.encode_utf16().chain([0]) {
+        option.extend_from_slice(&c.to_le_bytes());
+    }
+    option.extend_from_slice(&path);
+    option
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uefi::{guid, Guid};
+
+    #[test]
+    fn test_size_string() {
+        assert_eq!(size_string(512 * 1024 * 1024), "512 MiB");
+        assert_eq!(size_string(3 * 1024 * 1024 * 1024 / 2), "1.5 GiB");
+        assert_eq!(size_string(256 * 1024 * 1024 * 1024), "256.0 GiB");
+    }
+
+    #[test]
+    fn test_boot_config() {
+        let config = boot_config(false);
+        assert!(config.contains("default Rustux OS\n"));
+        assert!(config.contains("entry Rustux OS\n    kernel \\EFI\\Rustux\\kernel.elf\n"));
+        assert!(!config.contains("initrd"));
+        assert!(boot_config(true).ends_with("    initrd \\EFI\\Rustux\\bootfs.img\n"));
+    }
+
+    #[test]
+    fn test_boot_order() {
+        assert_eq!(boot_order(3, &[]), [3, 0]);
+        assert_eq!(boot_order(3, &[1, 2]), [3, 0, 1, 0, 2, 0]);
+        // An option already in the order moves to the front
+        assert_eq!(boot_order(0x102, &[1, 0x102, 2]), [2, 1, 1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_boot_option_name() {
+        assert_eq!(boot_option_name(0x1A), CString16::try_from("Boot001A").unwrap());
+        assert_eq!(boot_option_name(0xFFFF), CString16::try_from("BootFFFF").unwrap());
+    }
+
+    #[test]
+    fn test_load_option() {
+        let esp_guid = guid!("aaaaaaaa-bbbb-4ccc-8ddd-eeeeeeeeeeee");
+        let guids = [Guid::ZERO, esp_guid, Guid::ZERO];
+        let layout = Layout::with_guids(512, 8 * 1024 * 1024, ESP_SIZE, 0, guids).unwrap();
+        let option = load_option(&layout);
+
+        assert_eq!(option[0..4], LOAD_OPTION_ACTIVE.to_le_bytes());
+        let path_len = u16::from_le_bytes([option[4], option[5]]) as usize;
+        let title: Vec<u8> = SYSTEM_TITLE.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+        assert_eq!(option[6..6 + title.len()], title[..]);
+
+        let path = &option[6 + title.len()..];
+        assert_eq!(path.len(), path_len);
+
+        // Hard drive node naming the ESP
+        assert_eq!(path[0..4], [0x04, 0x01, 42, 0]);
+        assert_eq!(path[4..8], 1u32.to_le_bytes());
+        assert_eq!(path[8..16], 2048u64.to_le_bytes());
+        assert_eq!(path[16..24], layout.esp.blocks().to_le_bytes());
+        assert_eq!(path[24..40], esp_guid.to_bytes());
+        assert_eq!(path[40..42], [0x02, 0x02]);
+
+        // File path node, then the end node
+        let file: Vec<u8> = LOADER_PATH.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+        assert_eq!(path[42..44], [0x04, 0x04]);
+        assert_eq!(u16::from_le_bytes([path[44], path[45]]) as usize, 4 + file.len());
+        assert_eq!(path[46..46 + file.len()], file[..]);
+        assert_eq!(path[46 + file.len()..], [0x7F, 0xFF, 0x04, 0x00]);
+    }
+}
@@ -1,16 +1,25 @@
-#![no_std]
-#![no_main]
+// Unit tests run on the host, against std
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
 extern crate alloc;
 
 use uefi::prelude::*;
 use core::time::Duration;
 
+mod boot;
+mod disk;
+mod fat32;
+mod gpt;
+mod install;
+
 // Global allocator for UEFI
+#[cfg(not(test))]
 #[global_allocator]
 static ALLOCATOR: uefi::allocator::Allocator = uefi::allocator::Allocator;
 
 // Required for UEFI no_std
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     loop {
@@ -54,6 +63,11 @@ fn main() -> Status {
         None
     };
 
+    // The installer ends in a reboot unless the user backs out
+    if let Some(mode) = install_mode {
+        install::run(mode);
+    }
+
     uefi::system::with_stdout(|stdout| {
         let _ = stdout.set_color(uefi::proto::console::text::Color::White,
                                  uefi::proto::console::text::Color::Blue);
@@ -110,8 +124,8 @@ Boot Mode: INSTALLATION\r\n\
                     None => {}
                 }
                 let _ = stdout.output_string(cstr16!("\r\n\
-NOTE: Installation system coming soon...\r\n\
-System will boot in selected mode for now.\r\n\
+Installation cancelled.\r\n\
+System will boot in selected mode instead.\r\n\
 \r\n\
 Initializing system...\r\n\
 "));
@@ -123,12 +137,23 @@ Boot Mode: COMMAND LINE (CLI)\r\n\
   - Command-line interface only\r\n\
   - Minimal resource usage\r\n\
 \r\n\
-Initializing shell...\r\n\
+Starting the system loader...\r\n\
 "));
             }
         }
     });
 
+    // The shell runs on the booted system
+    if boot_mode == BootMode::CommandLine {
+        if let Err(e) = boot::start_loader() {
+            uefi::system::with_stdout(|stdout| {
+                let _ = stdout.set_color(uefi::proto::console::text::Color::LightRed,
+                                         uefi::proto::console::text::Color::Blue);
+                let _ = core::fmt::Write::write_fmt(stdout, format_args!("\r\nCould not boot: {}\r\n", e));
+            });
+        }
+    }
+
     // Continue to OS initialization
     // TODO: Transition to main OS loop based on boot mode
     // For now, keep system running in a loop
@@ -0,0 +1,357 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Code generation for `#[syscall_abi]`
+//!
+//! The syscall table lives in `abi/syscalls.abi` at the top of the tree.
+//! It opens with `#![version = N]`, the ABI version, followed by a list of
+//! `<name> = <number>;` entries, each with `///` docs and optionally
+//! `#[reserved]`. The whole file parses as Rust tokens.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::parse::{ParseStream, Parser};
+use syn::{Attribute, Expr, ExprLit, Ident, ItemEnum, Lit, LitInt, LitStr, Token};
+
+/// One syscall table entry
+struct Entry {
+    name: Ident,
+    docs: Vec<Attribute>,
+    number: u32,
+    /// Numbered but not implemented by the kernel
+    reserved: bool,
+}
+
+/// The parsed syscall table
+struct Table {
+    version: u32,
+    entries: Vec<Entry>,
+}
+
+/// Which side of the ABI an enum is generated for
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    /// `rx_<name>` variants for implemented syscalls, plus `from_raw`,
+    /// `name` and `dispatch`
+    Kernel,
+    /// CamelCase variants for every syscall
+    User,
+}
+
+/// Parse `"path"` or `"path", kernel`
+fn parse_attr(attr: TokenStream) -> syn::Result<(LitStr, Side)> {
+    let parser = |input: ParseStream| {
+        let path = input.parse::<LitStr>()?;
+        let mut side = Side::User;
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            let kind = input.parse::<Ident>()?;
+            if kind != "kernel" {
+                return Err(syn::Error::new(kind.span(), "expected `kernel`"));
+            }
+            side = Side::Kernel;
+        }
+        Ok((path, side))
+    };
+    parser.parse2(attr)
+}
+
+/// Parse the ABI version from `#![version = N]`
+fn parse_version(attrs: &[Attribute]) -> syn::Result<u32> {
+    let [attr] = attrs else {
+        return Err(syn::Error::new(Span::call_site(), "expected one `#![version = N]`"));
+    };
+    let version = match &attr.meta {
+        syn::Meta::NameValue(nv) if nv.path.is_ident("version") => match &nv.value {
+            Expr::Lit(ExprLit { lit: Lit::Int(lit), .. }) => lit.base10_parse::<u32>()?,
+            _ => return Err(syn::Error::new_spanned(&nv.value, "expected an integer")),
+        },
+        _ => return Err(syn::Error::new_spanned(attr, "expected `#![version = N]`")),
+    };
+    if version == 0 {
+        return Err(syn::Error::new_spanned(attr, "ABI versions start at 1"));
+    }
+    Ok(version)
+}
+
+/// Parse the syscall table
+fn parse_table(input: ParseStream) -> syn::Result<Table> {
+    let version = parse_version(&input.call(Attribute::parse_inner)?)?;
+    let mut entries: Vec<Entry> = Vec::new();
+    while !input.is_empty() {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let name = input.parse::<Ident>()?;
+        input.parse::<Token![=]>()?;
+        let number = input.parse::<LitInt>()?;
+        input.parse::<Token![;]>()?;
+
+        let mut docs = Vec::new();
+        let mut reserved = false;
+        for attr in attrs {
+            if attr.path().is_ident("doc") {
+                docs.push(attr);
+            } else if attr.path().is_ident("reserved") {
+                attr.meta.require_path_only()?;
+                reserved = true;
+            } else {
+                return Err(syn::Error::new_spanned(attr, "expected docs or `#[reserved]`"));
+            }
+        }
+
+        let number = number.base10_parse::<u32>()?;
+        if let Some(other) = entries.iter().find(|e| e.name == name || e.number == number) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("`{}` = {:#x} clashes with `{}` = {:#x}", name, number, other.name, other.number),
+            ));
+        }
+        entries.push(Entry { name, docs, number, reserved });
+    }
+    Ok(Table { version, entries })
+}
+
+/// `vmo_create` -> `VmoCreate`
+fn camel_case(name: &Ident) -> Ident {
+    let camel: String = name
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    Ident::new(&camel, name.span())
+}
+
+/// Discriminant of a hand-written variant, if it is an integer literal
+fn discriminant(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Int(lit), .. }) => lit.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn expand_syscall_abi(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let (path, side) = parse_attr(attr)?;
+    let item = syn::parse2::<ItemEnum>(item)?;
+
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let file = PathBuf::from(dir).join(path.value());
+    let source = std::fs::read_to_string(&file).map_err(|e| {
+        syn::Error::new(path.span(), format!("cannot read {}: {}", file.display(), e))
+    })?;
+    let table = parse_table
+        .parse_str(&source)
+        .map_err(|e| syn::Error::new(path.span(), format!("{}: {}", file.display(), e)))?;
+
+    let expanded = expand_enum(item, &table, side)?;
+
+    // Rebuild when the table changes
+    let file = file.to_string_lossy().into_owned();
+    Ok(quote! {
+        #expanded
+        const _: &[u8] = include_bytes!(#file);
+    })
+}
+
+/// Fill in `item` from the table
+fn expand_enum(mut item: ItemEnum, table: &Table, side: Side) -> syn::Result<TokenStream> {
+    let entries = &table.entries;
+    // Hand-written variants must not collide with the table
+    let mut taken: HashMap<u32, String> = HashMap::new();
+    for variant in &item.variants {
+        let number = variant
+            .discriminant
+            .as_ref()
+            .and_then(|(_, expr)| discriminant(expr))
+            .ok_or_else(|| syn::Error::new_spanned(variant, "expected an integer discriminant"))?;
+        taken.insert(number, variant.ident.to_string());
+    }
+    if side == Side::Kernel
+        && (item.variants.len() != 1 || item.variants[0].ident != "Unknown")
+    {
+        return Err(syn::Error::new_spanned(
+            &item.ident,
+            "the kernel enum takes only an `Unknown` variant; add syscalls to the ABI file",
+        ));
+    }
+    for entry in entries {
+        if let Some(other) = taken.get(&entry.number) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("`{}` = {:#x} clashes with variant `{}`", entry.name, entry.number, other),
+            ));
+        }
+    }
+
+    let generated = entries.iter().filter(|e| side == Side::User || !e.reserved);
+    let mut variants = syn::punctuated::Punctuated::<syn::Variant, Token![,]>::new();
+    for entry in generated.clone() {
+        let docs = &entry.docs;
+        let number = LitInt::new(&format!("{:#x}", entry.number), Span::call_site());
+        let reserved = entry
+            .reserved
+            .then(|| quote!(#[doc = ""] #[doc = "Reserved: not yet implemented by the kernel"]));
+        let ident = match side {
+            Side::Kernel => format_ident!("rx_{}", entry.name),
+            Side::User => camel_case(&entry.name),
+        };
+        variants.push(syn::parse_quote! {
+            #(#docs)*
+            #reserved
+            #ident = #number
+        });
+    }
+    variants.extend(item.variants);
+    item.variants = variants;
+
+    let ident = &item.ident;
+    let version = table.version;
+    let abi_version = quote! {
+        /// Syscall ABI version, bumped whenever syscalls are added
+        pub const ABI_VERSION: u32 = #version;
+    };
+
+    if side == Side::User {
+        return Ok(quote! {
+            #item
+
+            impl #ident {
+                #abi_version
+            }
+        });
+    }
+
+    let numbers: Vec<_> = generated
+        .clone()
+        .map(|e| LitInt::new(&format!("{:#x}", e.number), Span::call_site()))
+        .collect();
+    let variants: Vec<_> = generated.clone().map(|e| format_ident!("rx_{}", e.name)).collect();
+    let names: Vec<_> = variants.iter().map(|v| v.to_string()).collect();
+    let handlers: Vec<_> = generated.map(|e| format_ident!("sys_{}", e.name)).collect();
+
+    Ok(quote! {
+        #item
+
+        impl #ident {
+            #abi_version
+
+            /// Convert from raw number
+            ///
+            /// Reserved and unassigned numbers convert to `Unknown`.
+            pub const fn from_raw(n: u32) -> Self {
+                match n {
+                    #(#numbers => Self::#variants,)*
+                    _ => Self::Unknown,
+                }
+            }
+
+            /// Get the syscall name
+            pub const fn name(&self) -> &'static str {
+                match self {
+                    #(Self::#variants => #names,)*
+                    Self::Unknown => "unknown",
+                }
+            }
+
+            /// Run this syscall's handler, `sys_<name>`
+            ///
+            /// Returns `None` for `Unknown`.
+            pub fn dispatch(self, args: SyscallArgs) -> Option<SyscallRet> {
+                match self {
+                    #(Self::#variants => Some(#handlers(args)),)*
+                    Self::Unknown => None,
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "
+        #![version = 3]
+
+        /// Create new process under job
+        process_create = 0x01;
+
+        /// Sleep while a futex word holds a value
+        #[reserved]
+        futex_wait = 0x70;
+    ";
+
+    fn expand(side: Side, item: TokenStream) -> syn::Result<String> {
+        let table = parse_table.parse_str(TABLE)?;
+        let item = syn::parse2(item)?;
+        expand_enum(item, &table, side).map(|out| out.to_string())
+    }
+
+    #[test]
+    fn test_expand_kernel() {
+        let out = expand(Side::Kernel, quote!(pub enum SyscallNumber { Unknown = 0xFFFF })).unwrap();
+
+        assert!(out.contains("rx_process_create = 0x1"));
+        assert!(out.contains("0x1 => Self :: rx_process_create"));
+        assert!(out.contains("Self :: rx_process_create => Some (sys_process_create (args))"));
+        assert!(out.contains("Create new process under job"));
+        assert!(out.contains("pub const ABI_VERSION : u32 = 3u32"));
+        // Reserved syscalls stay out of the kernel
+        assert!(!out.contains("futex_wait"));
+    }
+
+    #[test]
+    fn test_expand_user() {
+        let out = expand(Side::User, quote!(pub enum SyscallNumber {})).unwrap();
+
+        assert!(out.contains("ProcessCreate = 0x1"));
+        assert!(out.contains("FutexWait = 0x70"));
+        assert!(out.contains("Reserved: not yet implemented by the kernel"));
+        assert!(out.contains("pub const ABI_VERSION : u32 = 3u32"));
+        assert!(!out.contains("fn dispatch"));
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let kernel = expand(Side::Kernel, quote!(pub enum SyscallNumber { Exit = 0x06 }));
+        assert!(kernel.unwrap_err().to_string().contains("only an `Unknown` variant"));
+
+        let clash = expand(Side::User, quote!(pub enum SyscallNumber { Legacy = 0x70 }));
+        assert!(clash.unwrap_err().to_string().contains("clashes with variant `Legacy`"));
+
+        let dup = parse_table.parse_str("#![version = 1] a = 0x01; b = 0x01;").err().unwrap();
+        assert!(dup.to_string().contains("`b` = 0x1 clashes with `a` = 0x1"));
+
+        let attr = parse_table.parse_str("#![version = 1] #[hidden] a = 0x01;").err().unwrap();
+        assert!(attr.to_string().contains("expected docs or `#[reserved]`"));
+
+        let version = parse_table.parse_str("a = 0x01;").err().unwrap();
+        assert!(version.to_string().contains("expected one `#![version = N]`"));
+    }
+
+    #[test]
+    fn test_abi_file() {
+        let source = include_str!("../../abi/syscalls.abi");
+        let table = parse_table.parse_str(source).unwrap();
+        let entries = &table.entries;
+
+        assert!(table.version >= 1);
+        assert!(entries.iter().any(|e| e.name == "vmar_map" && e.number == 0x14 && !e.reserved));
+        assert!(entries.iter().all(|e| e.number < 0xFFFF));
+    }
+
+    #[test]
+    fn test_camel_case() {
+        let name = Ident::new("pci_get_nth_device", Span::call_site());
+        assert_eq!(camel_case(&name), "PciGetNthDevice");
+    }
+}
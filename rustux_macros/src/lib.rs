@@ -4,19 +4,29 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT
 
-//! Procedural macros for the Rustux kernel test framework
+//! Procedural macros for the Rustux kernel test framework, IPC protocols
+//! and the syscall table
 
 extern crate proc_macro;
 
+mod abi;
+mod protocol;
+
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::{parse_macro_input, DeriveInput, Expr, ExprLit, ItemFn, Lit, Meta};
 
 /// Mark a function as a test case
 ///
 /// This attribute marks a function to be included in the test suite.
 /// The function must return `TestResult`.
 ///
+/// The function is left as written, and a `TestCase` named after its module
+/// path is placed in a `ktest.<path>` linker section, where the boot-time
+/// runner in `kernel::tests::runner` finds it. The first line of the doc
+/// comment becomes the description. Both only exist with the kernel's
+/// `ktest` feature, so production images carry no test code.
+///
 /// # Example
 ///
 /// ```rust
@@ -33,37 +43,35 @@ pub fn test_case(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let fn_name = &input.sig.ident;
     let fn_name_str = fn_name.to_string();
 
-    // Extract doc comments for description
-    let mut description = String::new();
-    for attr in &input.attrs {
-        if attr.path().is_ident("doc") {
-            if let Ok(doc) = attr.meta.require_list() {
-                let doc_str = doc.tokens.to_string();
-                if doc_str.starts_with('"') {
-                    let trimmed = doc_str.trim_matches('"').to_string();
-                    if !trimmed.is_empty() {
-                        description = trimmed;
-                    }
-                }
-            }
-        }
-    }
+    // `/// text` arrives as `#[doc = "text"]`; use the first non-empty line
+    let description = input
+        .attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
 
     let expanded = quote! {
-        #[allow(non_camel_case_types)]
-        #[cfg(test)]
-        fn #fn_name() -> crate::kernel::tests::runner::TestResult {
-            #input
-        }
+        #[cfg(feature = "ktest")]
+        #input
 
-        #[cfg(test)]
-        #[doc(hidden)]
-        const #fn_name: crate::kernel::tests::runner::TestCase =
-            crate::kernel::tests::runner::TestCase::new(
-                #fn_name_str,
-                &#description,
-                #fn_name,
-            );
+        #[cfg(feature = "ktest")]
+        const _: () = {
+            #[used]
+            #[link_section = concat!("ktest.", module_path!(), "::", #fn_name_str)]
+            static TEST: crate::kernel::tests::runner::TestCase =
+                crate::kernel::tests::runner::TestCase::new(
+                    concat!(module_path!(), "::", #fn_name_str),
+                    #description,
+                    #fn_name,
+                );
+        };
     };
 
     TokenStream::from(expanded)
@@ -78,3 +86,84 @@ pub fn test_registration_derive(_input: TokenStream) -> TokenStream {
     // Placeholder implementation
     TokenStream::new()
 }
+
+/// Declare a method-call protocol over channels
+///
+/// Applied to a trait whose methods each carry `#[ordinal(N)]`, take
+/// `&mut self` and return `Result<T>`. Arguments and return values must
+/// implement `ipc::protocol::Wire`; `Handle` arguments travel as
+/// transferred handles. A method added in a later version of the protocol
+/// is marked `#[ordinal(N, since = V)]`.
+///
+/// Alongside the trait, generates:
+///
+/// - `<Trait>Proxy`, a client with one method per trait method that sends
+///   the call over a channel and waits for the reply
+/// - `<Trait>Server<S>`, which implements `ipc::protocol::Dispatch` by
+///   decoding requests and calling `S`'s implementation of the trait
+///
+/// The wire format is described in `libipc`'s `protocol` module.
+///
+/// # Example
+///
+/// ```ignore
+/// #[ipc_protocol(version = 2)]
+/// pub trait Block {
+///     #[ordinal(1)]
+///     fn info(&mut self) -> Result<BlockInfo>;
+///     #[ordinal(2)]
+///     fn read(&mut self, block: u64, count: u32, vmo: Handle) -> Result<u32>;
+///     #[ordinal(3, since = 2)]
+///     fn flush(&mut self) -> Result<()>;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn ipc_protocol(attr: TokenStream, item: TokenStream) -> TokenStream {
+    protocol::expand_protocol(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `ipc::protocol::Wire` for a struct
+///
+/// Fields are encoded in declaration order.
+#[proc_macro_derive(Wire)]
+pub fn wire_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    protocol::expand_wire(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generate a `SyscallNumber` enum from the syscall table
+///
+/// Takes the path of the table, relative to the crate's manifest, so the
+/// kernel and libsys number syscalls from the same file. The table format
+/// is described at the top of `abi/syscalls.abi`.
+///
+/// By default every syscall becomes a CamelCase variant, as libsys names
+/// them. With `kernel`, only implemented syscalls are generated, as
+/// `rx_<name>` variants, and the enum gains `from_raw`, `name` and
+/// `dispatch`; `dispatch` calls `sys_<name>(args)`, so the kernel fails to
+/// build if a syscall in the table has no handler. The kernel enum must
+/// declare an `Unknown` variant, which reserved and unassigned numbers
+/// convert to.
+///
+/// # Example
+///
+/// ```ignore
+/// #[syscall_abi("abi/syscalls.abi", kernel)]
+/// #[repr(u32)]
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// #[allow(non_camel_case_types)]
+/// pub enum SyscallNumber {
+///     /// Unknown/invalid syscall number
+///     Unknown = 0xFFFF,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn syscall_abi(attr: TokenStream, item: TokenStream) -> TokenStream {
+    abi::expand_syscall_abi(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
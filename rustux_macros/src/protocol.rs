@@ -0,0 +1,362 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Code generation for `#[ipc_protocol]` and `#[derive(Wire)]`
+//!
+//! The generated code calls the runtime in `libipc`'s `protocol` module,
+//! reached as `::ipc::protocol`, and names status types through
+//! `::libsys`.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{ParseStream, Parser};
+use syn::{
+    Attribute, Data, DeriveInput, Fields, FnArg, GenericArgument, Ident, ItemTrait, LitInt, Pat,
+    PathArguments, ReturnType, Token, TraitItem, TraitItemFn, Type,
+};
+
+/// One protocol method
+struct Method {
+    name: Ident,
+    docs: Vec<Attribute>,
+    ordinal: u32,
+    since: u32,
+    args: Vec<(Ident, Type)>,
+    ret: Type,
+}
+
+/// Parse `version = N`, defaulting to 1
+fn parse_version(attr: TokenStream) -> syn::Result<u32> {
+    let mut version = 1;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("version") {
+            version = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("expected `version = N`"))
+        }
+    });
+    parser.parse2(attr)?;
+
+    if version == 0 {
+        return Err(syn::Error::new(proc_macro2::Span::call_site(), "protocol versions start at 1"));
+    }
+    Ok(version)
+}
+
+/// Parse `#[ordinal(N)]` or `#[ordinal(N, since = V)]`
+fn parse_ordinal(attr: &Attribute) -> syn::Result<(u32, u32)> {
+    attr.parse_args_with(|input: ParseStream| {
+        let ordinal = input.parse::<LitInt>()?;
+        let mut since = 1;
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            let key = input.parse::<Ident>()?;
+            if key != "since" {
+                return Err(syn::Error::new(key.span(), "expected `since = V`"));
+            }
+            input.parse::<Token![=]>()?;
+            since = input.parse::<LitInt>()?.base10_parse()?;
+        }
+        if ordinal.base10_parse::<u32>()? == 0 {
+            return Err(syn::Error::new(ordinal.span(), "ordinal 0 is reserved"));
+        }
+        Ok((ordinal.base10_parse()?, since))
+    })
+}
+
+/// `T` from a return type of `Result<T>`
+fn result_type(ret: &ReturnType) -> Option<Type> {
+    let ReturnType::Type(_, ty) = ret else { return None };
+    let Type::Path(path) = &**ty else { return None };
+    let last = path.path.segments.last()?;
+    if last.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else { return None };
+    match args.args.first()? {
+        GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+impl Method {
+    /// Parse a trait method, removing its `#[ordinal]` attribute
+    fn parse(func: &mut TraitItemFn, version: u32) -> syn::Result<Self> {
+        let sig = &func.sig;
+        let position = func.attrs.iter().position(|a| a.path().is_ident("ordinal")).ok_or_else(|| {
+            syn::Error::new_spanned(&sig.ident, "protocol methods need an #[ordinal(N)] attribute")
+        })?;
+        let attr = func.attrs.remove(position);
+        let (ordinal, since) = parse_ordinal(&attr)?;
+        if since == 0 || since > version {
+            return Err(syn::Error::new_spanned(
+                &attr,
+                format!("`since` must be between 1 and the protocol version, {}", version),
+            ));
+        }
+
+        if !sig.generics.params.is_empty() || sig.asyncness.is_some() {
+            return Err(syn::Error::new_spanned(sig, "protocol methods can't be generic or async"));
+        }
+
+        let mut inputs = sig.inputs.iter();
+        match inputs.next() {
+            Some(FnArg::Receiver(recv)) if recv.reference.is_some() && recv.mutability.is_some() => {}
+            _ => return Err(syn::Error::new_spanned(sig, "protocol methods take `&mut self`")),
+        }
+
+        let mut args = Vec::new();
+        for input in inputs {
+            let FnArg::Typed(arg) = input else { unreachable!() };
+            let Pat::Ident(pat) = &*arg.pat else {
+                return Err(syn::Error::new_spanned(&arg.pat, "protocol arguments must be plain names"));
+            };
+            args.push((pat.ident.clone(), (*arg.ty).clone()));
+        }
+
+        let ret = result_type(&sig.output)
+            .ok_or_else(|| syn::Error::new_spanned(&sig.output, "protocol methods must return Result<T>"))?;
+
+        Ok(Self {
+            name: sig.ident.clone(),
+            docs: func.attrs.iter().filter(|a| a.path().is_ident("doc")).cloned().collect(),
+            ordinal,
+            since,
+            args,
+            ret,
+        })
+    }
+
+    /// Proxy method sending this call
+    fn proxy(&self) -> TokenStream {
+        let Method { name, docs, ordinal, since, ret, .. } = self;
+        let names = self.args.iter().map(|(name, _)| name);
+        let params = self.args.iter().map(|(name, ty)| quote! { #name: #ty });
+
+        quote! {
+            #(#docs)*
+            pub fn #name(&mut self, #(#params),*) -> ::libsys::Result<#ret> {
+                let mut __args = ::ipc::protocol::Encoder::new();
+                #(::ipc::protocol::Wire::encode(#names, &mut __args);)*
+                self.client.call(#ordinal, #since, __args)
+            }
+        }
+    }
+
+    /// Dispatch match arm calling this method on the server
+    fn dispatch_arm(&self) -> TokenStream {
+        let Method { name, ordinal, .. } = self;
+        let names: Vec<_> = self.args.iter().map(|(name, _)| name).collect();
+        let types = self.args.iter().map(|(_, ty)| ty);
+
+        quote! {
+            #ordinal => {
+                #(let #names = <#types as ::ipc::protocol::Wire>::decode(__args)?;)*
+                __args.finish()?;
+                let __ret = self.0.#name(#(#names),*)?;
+                let mut __reply = ::ipc::protocol::Encoder::new();
+                ::ipc::protocol::Wire::encode(__ret, &mut __reply);
+                Ok(__reply)
+            }
+        }
+    }
+}
+
+/// Expand `#[ipc_protocol]` on a trait
+pub fn expand_protocol(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let version = parse_version(attr)?;
+    let mut item: ItemTrait = syn::parse2(item)?;
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "protocols can't be generic"));
+    }
+
+    let mut methods: Vec<Method> = Vec::new();
+    for trait_item in &mut item.items {
+        let TraitItem::Fn(func) = trait_item else {
+            return Err(syn::Error::new_spanned(trait_item, "protocols may only contain methods"));
+        };
+        let method = Method::parse(func, version)?;
+        if let Some(other) = methods.iter().find(|m| m.ordinal == method.ordinal) {
+            return Err(syn::Error::new_spanned(
+                &func.sig.ident,
+                format!("ordinal {} is already used by `{}`", method.ordinal, other.name),
+            ));
+        }
+        methods.push(method);
+    }
+
+    let vis = &item.vis;
+    let name = &item.ident;
+    let proxy = format_ident!("{}Proxy", name);
+    let server = format_ident!("{}Server", name);
+    let proxy_methods = methods.iter().map(Method::proxy);
+    let dispatch_arms = methods.iter().map(Method::dispatch_arm);
+    let proxy_doc = format!("Client proxy for the `{}` protocol", name);
+    let server_doc = format!("Serves the `{}` protocol with an implementation of the trait", name);
+
+    Ok(quote! {
+        #item
+
+        #[doc = #proxy_doc]
+        #vis struct #proxy {
+            client: ::ipc::protocol::Client,
+        }
+
+        impl #proxy {
+            /// Protocol version this proxy was generated from
+            pub const VERSION: u32 = #version;
+
+            /// Call the protocol over `channel`
+            pub fn new(channel: ::ipc::Channel) -> Self {
+                Self::with_peer_version(channel, Self::VERSION)
+            }
+
+            /// Call the protocol over `channel` to a server that speaks
+            /// `peer_version`; methods newer than it fail with `NotSupported`
+            pub fn with_peer_version(channel: ::ipc::Channel, peer_version: u32) -> Self {
+                let version = ::core::cmp::min(Self::VERSION, peer_version);
+                Self { client: ::ipc::protocol::Client::new(channel, version) }
+            }
+
+            /// The underlying client
+            pub fn client(&self) -> &::ipc::protocol::Client {
+                &self.client
+            }
+
+            #(#proxy_methods)*
+        }
+
+        #[doc = #server_doc]
+        #vis struct #server<S>(pub S);
+
+        impl<S: #name> ::ipc::protocol::Dispatch for #server<S> {
+            const VERSION: u32 = #version;
+
+            fn dispatch(
+                &mut self,
+                __ordinal: u32,
+                __args: &mut ::ipc::protocol::Decoder,
+            ) -> ::libsys::Result<::ipc::protocol::Encoder> {
+                match __ordinal {
+                    #(#dispatch_arms)*
+                    _ => Err(::libsys::Error::new(::libsys::Status::NotSupported)),
+                }
+            }
+        }
+    })
+}
+
+/// Expand `#[derive(Wire)]` on a struct
+pub fn expand_wire(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "Wire can only be derived for structs"));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (encode, decode) = match &data.fields {
+        Fields::Named(fields) => {
+            let names: Vec<_> = fields.named.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+            (
+                quote! { #(::ipc::protocol::Wire::encode(self.#names, __enc);)* },
+                quote! { Self { #(#names: ::ipc::protocol::Wire::decode(__dec)?),* } },
+            )
+        }
+        Fields::Unnamed(fields) => {
+            let indices: Vec<_> = (0..fields.unnamed.len()).map(syn::Index::from).collect();
+            let decodes = indices.iter().map(|_| quote! { ::ipc::protocol::Wire::decode(__dec)? });
+            (
+                quote! { #(::ipc::protocol::Wire::encode(self.#indices, __enc);)* },
+                quote! { Self(#(#decodes),*) },
+            )
+        }
+        Fields::Unit => (quote! {}, quote! { Self }),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::ipc::protocol::Wire for #name #ty_generics #where_clause {
+            fn encode(self, __enc: &mut ::ipc::protocol::Encoder) {
+                #encode
+            }
+
+            fn decode(__dec: &mut ::ipc::protocol::Decoder) -> ::libsys::Result<Self> {
+                Ok(#decode)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_err(attr: TokenStream, item: TokenStream) -> String {
+        expand_protocol(attr, item).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_expand_protocol() {
+        let item = quote! {
+            pub trait Block {
+                /// Read blocks into `vmo`
+                #[ordinal(2)]
+                fn read(&mut self, block: u64, vmo: Handle) -> Result<u32>;
+                #[ordinal(3, since = 2)]
+                fn flush(&mut self) -> Result<()>;
+            }
+        };
+        let out = expand_protocol(quote!(version = 2), item).unwrap().to_string();
+
+        assert!(out.contains("pub struct BlockProxy"));
+        assert!(out.contains("pub struct BlockServer"));
+        assert!(out.contains("const VERSION : u32 = 2u32"));
+        assert!(out.contains("self . client . call (3u32 , 2u32 , __args)"));
+        assert!(out.contains("Read blocks into `vmo`"));
+        // The ordinal attribute is consumed
+        assert!(!out.contains("# [ordinal"));
+    }
+
+    #[test]
+    fn test_expand_protocol_errors() {
+        let missing = quote! { trait P { fn a(&mut self) -> Result<()>; } };
+        assert!(expand_err(quote!(), missing).contains("#[ordinal(N)]"));
+
+        let duplicate = quote! {
+            trait P {
+                #[ordinal(1)] fn a(&mut self) -> Result<()>;
+                #[ordinal(1)] fn b(&mut self) -> Result<()>;
+            }
+        };
+        assert!(expand_err(quote!(), duplicate).contains("already used by `a`"));
+
+        let future = quote! { trait P { #[ordinal(1, since = 3)] fn a(&mut self) -> Result<()>; } };
+        assert!(expand_err(quote!(version = 2), future).contains("since"));
+
+        let shared = quote! { trait P { #[ordinal(1)] fn a(&self) -> Result<()>; } };
+        assert!(expand_err(quote!(), shared).contains("&mut self"));
+
+        let bare = quote! { trait P { #[ordinal(1)] fn a(&mut self) -> u32; } };
+        assert!(expand_err(quote!(), bare).contains("Result<T>"));
+
+        let reserved = quote! { trait P { #[ordinal(0)] fn a(&mut self) -> Result<()>; } };
+        assert!(expand_err(quote!(), reserved).contains("reserved"));
+    }
+
+    #[test]
+    fn test_expand_wire() {
+        let named: DeriveInput = syn::parse2(quote! { struct Info { size: u64, flags: u32 } }).unwrap();
+        let out = expand_wire(named).unwrap().to_string();
+        assert!(out.contains("self . size"));
+        assert!(out.contains("flags : :: ipc :: protocol :: Wire :: decode"));
+
+        let tuple: DeriveInput = syn::parse2(quote! { struct Id(u32); }).unwrap();
+        assert!(expand_wire(tuple).unwrap().to_string().contains("self . 0"));
+
+        let sum: DeriveInput = syn::parse2(quote! { enum E { A } }).unwrap();
+        assert!(expand_wire(sum).is_err());
+    }
+}
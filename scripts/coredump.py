@@ -0,0 +1,145 @@
+#!/usr/bin/env python3
+# Copyright 2025 The Rustux Authors
+#
+# Use of this source code is governed by a MIT-style
+# license that can be found in the LICENSE file or at
+# https://opensource.org/licenses/MIT
+
+"""Pull core dumps out of a serial log and print what they hold.
+
+crashsvc logs each dump base64-encoded between {{{coredump:begin:...}}}
+and {{{coredump:end}}} lines. The format is described under
+rx_thread_write_coredump in docs/syscall_abi_spec.md.
+
+Usage:
+  coredump.py extract <serial.log> [outdir]   write core.<pid>.<n> files
+  coredump.py show <core>                     print a dump's contents
+"""
+
+import base64
+import os
+import re
+import struct
+import sys
+
+CORE_MAGIC = b"RXCORE\0\0"
+CORE_FLAG_TRUNCATED = 1
+
+STREAM_EXCEPTION = 1
+STREAM_THREAD = 2
+STREAM_MAPPINGS = 3
+STREAM_HANDLES = 4
+STREAM_MEMORY = 5
+
+EXCEPTION_KINDS = {
+    0x1: "hardware breakpoint",
+    0x2: "software breakpoint",
+    0x4: "undefined instruction",
+    0x8: "general fault",
+    0x20: "page fault",
+}
+
+HW_BREAKPOINT_SUBTYPES = {
+    1: "single step",
+    2: "hardware breakpoint",
+    3: "watchpoint",
+}
+
+BEGIN = re.compile(r"\{\{\{coredump:begin:(\d+):(\d+)\}\}\}")
+END = "{{{coredump:end}}}"
+
+
+def extract(log_path, outdir):
+    os.makedirs(outdir, exist_ok=True)
+    count = 0
+    dump = None
+    with open(log_path, errors="replace") as log:
+        for line in log:
+            line = line.strip()
+            begin = BEGIN.search(line)
+            if begin:
+                pid, size = int(begin.group(1)), int(begin.group(2))
+                dump = []
+            elif dump is not None and line.endswith(END):
+                data = base64.b64decode("".join(dump))
+                path = os.path.join(outdir, "core.%d.%d" % (pid, count))
+                with open(path, "wb") as out:
+                    out.write(data)
+                note = "" if len(data) == size else " (expected %d, log cut short?)" % size
+                print("%s: %d bytes%s" % (path, len(data), note))
+                count += 1
+                dump = None
+            elif dump is not None:
+                dump.append(line)
+    if count == 0:
+        sys.exit("coredump: no dumps in %s" % log_path)
+
+
+def records(data, entry, fmt):
+    kind, count, offset, size = entry
+    step = struct.calcsize(fmt)
+    return [struct.unpack_from(fmt, data, offset + i * step) for i in range(count)]
+
+
+def show(path):
+    with open(path, "rb") as f:
+        data = f.read()
+    if data[:8] != CORE_MAGIC:
+        sys.exit("coredump: %s is not a core dump" % path)
+
+    version, flags, stream_count, _, directory, size = struct.unpack_from("<IIIIQQ", data, 8)
+    print("core dump version %d, %d bytes%s" %
+          (version, size, ", memory truncated" if flags & CORE_FLAG_TRUNCATED else ""))
+    streams = {}
+    for i in range(stream_count):
+        entry = struct.unpack_from("<IIQQ", data, directory + i * 24)
+        streams[entry[0]] = entry
+
+    if STREAM_EXCEPTION in streams:
+        # Reports before arch_status was added are 80 bytes
+        entry = streams[STREAM_EXCEPTION]
+        fmt = "<IIQQQQQQQQQQ" if entry[3] >= 88 else "<IIQQQQQQQQQ"
+        report = records(data, entry, fmt)[0] + (0,)
+        (kind, subtype, pid, tid, process_koid, thread_koid, pc, sp, fp, fault_addr,
+         err_code, arch_status) = report[:12]
+        name = EXCEPTION_KINDS.get(kind, "exception %#x" % kind)
+        if kind == 0x1:
+            name = HW_BREAKPOINT_SUBTYPES.get(subtype, name)
+        print("%s in process %d (koid %d) thread %d (koid %d)" %
+              (name, pid, process_koid, tid, thread_koid))
+        print("  fault address %#x, error %#x, arch status %#x" % (fault_addr, err_code, arch_status))
+
+    for record in records(data, streams.get(STREAM_THREAD, (0, 0, 0, 0)), "<QQ36Q"):
+        tid, koid, pc, sp, fp, flags = record[:6]
+        print("thread %d (koid %d): pc %#x sp %#x fp %#x flags %#x" % (tid, koid, pc, sp, fp, flags))
+        regs = record[6:]
+        for row in range(0, 32, 4):
+            print("  " + "  ".join("r%-2d %#018x" % (row + i, regs[row + i]) for i in range(4)))
+
+    print("mappings:")
+    for base, size, vmo_koid, vmo_offset, prot, _ in records(
+            data, streams.get(STREAM_MAPPINGS, (0, 0, 0, 0)), "<QQQQII"):
+        perms = "".join(c if prot & bit else "-" for c, bit in (("r", 1), ("w", 2), ("x", 4)))
+        print("  %#014x-%#014x %s vmo koid %d +%#x" % (base, base + size, perms, vmo_koid, vmo_offset))
+
+    print("handles:")
+    for handle, type_, koid, related, rights, _ in records(
+            data, streams.get(STREAM_HANDLES, (0, 0, 0, 0)), "<IIQQII"):
+        print("  %#010x type %d koid %d related %d rights %#x" % (handle, type_, koid, related, rights))
+
+    print("memory:")
+    for base, size, offset in records(data, streams.get(STREAM_MEMORY, (0, 0, 0, 0)), "<QQQ"):
+        print("  %#014x-%#014x at %#x" % (base, base + size, offset))
+
+
+def main():
+    if len(sys.argv) >= 3 and sys.argv[1] == "extract":
+        extract(sys.argv[2], sys.argv[3] if len(sys.argv) > 3 else ".")
+    elif len(sys.argv) == 3 and sys.argv[1] == "show":
+        show(sys.argv[2])
+    else:
+        sys.exit(__doc__.strip())
+
+
+if __name__ == "__main__":
+    main()
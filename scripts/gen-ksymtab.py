@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+# Copyright 2025 The Rustux Authors
+#
+# Use of this source code is governed by a MIT-style
+# license that can be found in the LICENSE file or at
+# https://opensource.org/licenses/MIT
+
+"""Embed the kernel symbol table into a linked kernel image.
+
+Fills the KSYMTAB buffer (src/kernel/lib/symbolize.rs) in place with the
+image's function symbols, so panic backtraces can be symbolized.
+
+Usage: gen-ksymtab.py <kernel.elf>
+"""
+
+import shutil
+import struct
+import subprocess
+import sys
+
+KSYMTAB_MAGIC = 0x4D59534B  # "KSYM"
+KSYMTAB_SYMBOL = b"KSYMTAB"
+HEADER_SIZE = 16
+ENTRY_SIZE = 16
+MAX_NAME_LEN = 127
+
+SHT_SYMTAB = 2
+SHT_NOBITS = 8
+STT_FUNC = 2
+
+
+def read_sections(elf):
+    if elf[:4] != b"\x7fELF" or elf[4] != 2 or elf[5] != 1:
+        sys.exit("gen-ksymtab: not a little-endian ELF64 file")
+
+    shoff, = struct.unpack_from("<Q", elf, 0x28)
+    shentsize, shnum = struct.unpack_from("<HH", elf, 0x3A)
+    sections = []
+    for i in range(shnum):
+        (name, type_, flags, addr, offset, size, link, info, align,
+         entsize) = struct.unpack_from("<IIQQQQIIQQ", elf, shoff + i * shentsize)
+        sections.append(dict(type=type_, addr=addr, offset=offset, size=size,
+                             link=link, entsize=entsize))
+    return sections
+
+
+def read_symbols(elf, sections):
+    symtab = next((s for s in sections if s["type"] == SHT_SYMTAB), None)
+    if symtab is None:
+        sys.exit("gen-ksymtab: image has no symbol table (stripped?)")
+    strtab = sections[symtab["link"]]
+
+    for off in range(symtab["offset"], symtab["offset"] + symtab["size"], 24):
+        name_off, info, _other, shndx, value, size = struct.unpack_from("<IBBHQQ", elf, off)
+        start = strtab["offset"] + name_off
+        name = elf[start:elf.index(b"\0", start)]
+        yield name, info & 0xF, shndx, value, size
+
+
+def demangle(names):
+    tool = shutil.which("rustfilt") or shutil.which("llvm-cxxfilt") or shutil.which("c++filt")
+    if tool is None:
+        return names
+    out = subprocess.run([tool], input="\n".join(names), capture_output=True,
+                         text=True, check=True).stdout.splitlines()
+    return out if len(out) == len(names) else names
+
+
+def build_table(funcs, capacity):
+    entries = bytearray()
+    strtab = bytearray()
+    count = 0
+    for addr, size, name in funcs:
+        encoded = name.encode()[:MAX_NAME_LEN] + b"\0"
+        needed = HEADER_SIZE + (count + 1) * ENTRY_SIZE + len(strtab) + len(encoded)
+        if needed > capacity:
+            print("gen-ksymtab: table full, dropped %d symbols" % (len(funcs) - count),
+                  file=sys.stderr)
+            break
+        entries += struct.pack("<QII", addr, min(size, 0xFFFFFFFF), len(strtab))
+        strtab += encoded
+        count += 1
+
+    header = struct.pack("<IIII", KSYMTAB_MAGIC, count, len(strtab), 0)
+    return header + entries + strtab, count
+
+
+def main():
+    if len(sys.argv) != 2:
+        sys.exit(__doc__)
+    path = sys.argv[1]
+
+    with open(path, "rb") as f:
+        elf = bytearray(f.read())
+    sections = read_sections(elf)
+
+    table = None
+    funcs = {}
+    for name, type_, shndx, value, size in read_symbols(elf, sections):
+        if name == KSYMTAB_SYMBOL:
+            table = (shndx, value, size)
+        elif type_ == STT_FUNC and value != 0 and name:
+            funcs.setdefault(value, (size, name.decode(errors="replace")))
+
+    if table is None:
+        sys.exit("gen-ksymtab: KSYMTAB not found")
+    shndx, vaddr, capacity = table
+    section = sections[shndx]
+    if section["type"] == SHT_NOBITS:
+        sys.exit("gen-ksymtab: KSYMTAB has no file contents")
+    file_off = section["offset"] + vaddr - section["addr"]
+
+    addrs = sorted(funcs)
+    names = demangle([funcs[a][1] for a in addrs])
+    ordered = [(a, funcs[a][0], n) for a, n in zip(addrs, names)]
+
+    blob, count = build_table(ordered, capacity)
+    elf[file_off:file_off + len(blob)] = blob
+
+    with open(path, "wb") as f:
+        f.write(elf)
+    print("gen-ksymtab: embedded %d symbols (%d bytes) in %s" % (count, len(blob), path))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,136 @@
+#!/usr/bin/env python3
+# Copyright 2025 The Rustux Authors
+#
+# Use of this source code is governed by a MIT-style
+# license that can be found in the LICENSE file or at
+# https://opensource.org/licenses/MIT
+
+"""Sign kernel and initrd images for uefi-loader verified boot.
+
+Writes Ed25519 (RFC 8032) detached signatures, checked by
+uefi-loader/src/secureboot.rs: <image>.sig holds the 64 raw signature
+bytes. The key file holds the 32-byte private seed as hex.
+
+Usage: sign-boot-image.py genkey <key>
+       sign-boot-image.py pubkey <key>
+       sign-boot-image.py sign <key> <image>...
+
+`pubkey` prints the value to build the loader with RUSTUX_BOOT_PUBKEY.
+"""
+
+import hashlib
+import os
+import sys
+
+P = 2**255 - 19
+L = 2**252 + 27742317777372353535851937790883648493
+D = -121665 * pow(121666, P - 2, P) % P
+SQRT_M1 = pow(2, (P - 1) // 4, P)
+
+
+def sha512_int(*parts):
+    return int.from_bytes(hashlib.sha512(b"".join(parts)).digest(), "little")
+
+
+def point_add(a, b):
+    # Extended coordinates, RFC 8032 5.1.4
+    x1, y1, z1, t1 = a
+    x2, y2, z2, t2 = b
+    pa = (y1 - x1) * (y2 - x2) % P
+    pb = (y1 + x1) * (y2 + x2) % P
+    pc = 2 * t1 * t2 * D % P
+    pd = 2 * z1 * z2 % P
+    e, f, g, h = pb - pa, pd - pc, pd + pc, pb + pa
+    return (e * f % P, g * h % P, f * g % P, e * h % P)
+
+
+def point_mul(s, point):
+    result = (0, 1, 1, 0)
+    while s > 0:
+        if s & 1:
+            result = point_add(result, point)
+        point = point_add(point, point)
+        s >>= 1
+    return result
+
+
+def point_encode(point):
+    x, y, z, _ = point
+    zinv = pow(z, P - 2, P)
+    x, y = x * zinv % P, y * zinv % P
+    return (y | (x & 1) << 255).to_bytes(32, "little")
+
+
+def base_point():
+    y = 4 * pow(5, P - 2, P) % P
+    u, v = (y * y - 1) % P, (D * y * y + 1) % P
+    x = pow(u * pow(v, P - 2, P), (P + 3) // 8, P)
+    if (x * x - u * pow(v, P - 2, P)) % P != 0:
+        x = x * SQRT_M1 % P
+    if x & 1:
+        x = P - x
+    return (x, y, 1, x * y % P)
+
+
+B = base_point()
+
+
+def expand_seed(seed):
+    h = hashlib.sha512(seed).digest()
+    a = int.from_bytes(h[:32], "little")
+    a &= (1 << 254) - 8
+    a |= 1 << 254
+    return a, h[32:]
+
+
+def public_key(seed):
+    a, _ = expand_seed(seed)
+    return point_encode(point_mul(a, B))
+
+
+def sign(seed, message):
+    a, prefix = expand_seed(seed)
+    pub = point_encode(point_mul(a, B))
+    r = sha512_int(prefix, message) % L
+    rs = point_encode(point_mul(r, B))
+    k = sha512_int(rs, pub, message) % L
+    s = (r + k * a) % L
+    return rs + s.to_bytes(32, "little")
+
+
+def read_key(path):
+    with open(path) as f:
+        seed = bytes.fromhex(f.read().strip())
+    if len(seed) != 32:
+        sys.exit("sign-boot-image: %s is not a 32-byte hex key" % path)
+    return seed
+
+
+def main():
+    if len(sys.argv) < 3:
+        sys.exit(__doc__)
+    command, key_path = sys.argv[1], sys.argv[2]
+
+    if command == "genkey" and len(sys.argv) == 3:
+        if os.path.exists(key_path):
+            sys.exit("sign-boot-image: %s already exists" % key_path)
+        fd = os.open(key_path, os.O_WRONLY | os.O_CREAT | os.O_EXCL, 0o600)
+        with os.fdopen(fd, "w") as f:
+            f.write(os.urandom(32).hex() + "\n")
+        print("sign-boot-image: wrote %s" % key_path)
+    elif command == "pubkey" and len(sys.argv) == 3:
+        print(public_key(read_key(key_path)).hex())
+    elif command == "sign" and len(sys.argv) > 3:
+        seed = read_key(key_path)
+        for image in sys.argv[3:]:
+            with open(image, "rb") as f:
+                signature = sign(seed, f.read())
+            with open(image + ".sig", "wb") as f:
+                f.write(signature)
+            print("sign-boot-image: signed %s" % image)
+    else:
+        sys.exit(__doc__)
+
+
+if __name__ == "__main__":
+    main()
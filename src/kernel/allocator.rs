@@ -8,12 +8,16 @@
 //!
 //! This module provides a linked list allocator for the kernel heap.
 //! It supports allocation, deallocation, and memory reuse.
+//!
+//! With the `kasan` feature, every block is tracked in shadow memory and
+//! freed blocks pass through a quarantine first (see [`kasan`]).
 
 
+use crate::kernel::kasan;
 use alloc::alloc::{GlobalAlloc, Layout};
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-const HEAP_SIZE: usize = 16 * 1024 * 1024; // 16 MB heap
+pub(crate) const HEAP_SIZE: usize = 16 * 1024 * 1024; // 16 MB heap
 
 #[repr(align(16))]
 struct AlignedHeap {
@@ -38,6 +42,9 @@ struct BlockHeader {
     next: Option<*mut BlockHeader>,
 }
 
+/// Bytes before each allocation
+pub(crate) const HEADER_SIZE: usize = core::mem::size_of::<BlockHeader>();
+
 impl BlockHeader {
     /// Get the end of this block
     fn end(&self) -> *mut u8 {
@@ -55,10 +62,10 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let size = layout.size();
         let align = layout.align();
+        let _quiet = kasan::Quiet::new();
 
-        // Calculate aligned size (at least size of BlockHeader)
-        let block_size = core::cmp::max(size, core::mem::size_of::<BlockHeader>())
-            .next_power_of_two();
+        // Calculate aligned size, counting the header and any redzone
+        let block_size = (HEADER_SIZE + size + kasan::REDZONE).next_power_of_two();
 
         // Get the free list head
         let mut free_list = self.free_list.load(Ordering::Acquire) as *mut BlockHeader;
@@ -104,7 +111,9 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
                 }
 
                 // Return pointer after the header
-                return (current as usize + core::mem::size_of::<BlockHeader>()) as *mut u8;
+                let ptr = (current as usize + HEADER_SIZE) as *mut u8;
+                kasan::on_alloc(ptr, size, (*current).size - HEADER_SIZE);
+                return ptr;
             }
 
             prev = current;
@@ -121,9 +130,17 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
         if ptr.is_null() {
             return;
         }
+        let _quiet = kasan::Quiet::new();
 
         // Get the block header
-        let block = (ptr as usize - core::mem::size_of::<BlockHeader>()) as *mut BlockHeader;
+        let block = (ptr as usize - HEADER_SIZE) as *mut BlockHeader;
+
+        // Free whatever block leaves quarantine instead
+        let ptr = kasan::on_free(ptr, (*block).size - HEADER_SIZE);
+        if ptr.is_null() {
+            return;
+        }
+        let block = (ptr as usize - HEADER_SIZE) as *mut BlockHeader;
 
         // Mark block as free
         (*block).free = true;
@@ -176,18 +193,20 @@ pub fn init() {
         let heap_start = HEAP.data.as_ptr() as usize;
         let block = heap_start as *mut BlockHeader;
 
-        (*block).size = HEAP_SIZE - core::mem::size_of::<BlockHeader>();
+        (*block).size = HEAP_SIZE - HEADER_SIZE;
         (*block).free = true;
         (*block).prev = None;
         (*block).next = None;
 
         ALLOCATOR.free_list.store(block as usize, Ordering::Release);
+        kasan::init(heap_start, HEAP_SIZE);
     }
 }
 
 /// Get heap usage statistics
 pub fn heap_usage() -> usize {
     let mut used = 0usize;
+    let _quiet = kasan::Quiet::new();
     unsafe {
         let mut current = ALLOCATOR.free_list.load(Ordering::Acquire) as *mut BlockHeader;
 
@@ -210,3 +229,17 @@ pub fn heap_usage() -> usize {
 pub fn heap_size() -> usize {
     HEAP_SIZE
 }
+
+/// `heap` console command
+fn cmd_heap(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    let used = heap_usage();
+    crate::println!(
+        "heap: {} KiB used of {} KiB ({}%)",
+        used >> 10,
+        HEAP_SIZE >> 10,
+        used * 100 / HEAP_SIZE
+    );
+    0
+}
+
+crate::static_command!("heap", "show kernel heap usage", cmd_heap);
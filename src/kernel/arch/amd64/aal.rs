@@ -12,6 +12,7 @@
 
 use crate::kernel::arch::arch_traits::*;
 use crate::kernel::arch::amd64;
+use crate::kernel::object::exception::DebugRegs;
 use crate::rustux::types::*;
 
 /// Marker type for AMD64 architecture
@@ -48,7 +49,7 @@ impl ArchThreadContext for Amd64Arch {
         arg: usize,
         stack_top: VAddr,
     ) {
-        amd64::arch_thread_initialize(thread);
+        amd64::arch_thread_initialize(thread, stack_top);
     }
 
     unsafe fn save_context(context: &mut Self::Context) {
@@ -724,48 +725,139 @@ impl ArchDebug for Amd64Arch {
             *slot = false;
         }
     }
+
+    fn user_breakpoint_count() -> u32 {
+        MAX_HW_BREAKPOINTS as u32
+    }
+
+    fn read_user_breakpoints(thread: &crate::kernel::thread::Thread, regs: &mut DebugRegs) {
+        let state = &thread.arch.debug_state;
+        regs.hw_bps = [state.dr0, state.dr1, state.dr2, state.dr3];
+        regs.enabled = (0..MAX_HW_BREAKPOINTS)
+            .filter(|&slot| state.dr7 & (1 << (slot * 2)) != 0)
+            .fold(0, |enabled, slot| enabled | 1 << slot);
+        regs.status = state.dr6;
+    }
+
+    unsafe fn write_user_breakpoints(thread: &crate::kernel::thread::Thread, regs: &DebugRegs) -> i32 {
+        // Local enable only; R/W and LEN of 00 make each an instruction
+        // breakpoint
+        let mut dr7 = 0u64;
+        for slot in 0..MAX_HW_BREAKPOINTS {
+            if regs.enabled & (1 << slot) == 0 {
+                continue;
+            }
+            if !Self::is_user_address(regs.hw_bps[slot] as VAddr) {
+                return -1;
+            }
+            dr7 |= 1 << (slot * 2);
+        }
+        if regs.enabled >> MAX_HW_BREAKPOINTS != 0 {
+            return -1;
+        }
+
+        // Loaded into the CPU when the thread is next switched in
+        let arch = &thread.arch as *const crate::kernel::thread::ArchData as *mut crate::kernel::thread::ArchData;
+        (*arch).debug_state = amd64::registers::X86DebugState {
+            dr0: regs.hw_bps[0],
+            dr1: regs.hw_bps[1],
+            dr2: regs.hw_bps[2],
+            dr3: regs.hw_bps[3],
+            dr6: (*arch).debug_state.dr6,
+            dr7,
+        };
+        (*arch).track_debug_state = true;
+        0
+    }
+}
+
+// ============= ArchPerfmon Implementation =============
+
+impl ArchPerfmon for Amd64Arch {
+    fn perfmon_counter_count() -> u32 {
+        amd64::perfmon::x86_perfmon_counter_count()
+    }
+
+    fn perfmon_counter_bits() -> u32 {
+        amd64::perfmon::x86_perfmon_counter_bits()
+    }
+
+    fn perfmon_event(event: u32) -> Option<u64> {
+        amd64::perfmon::x86_perfmon_event(event)
+    }
+
+    unsafe fn perfmon_init() {
+        amd64::perfmon::x86_perfmon_init();
+    }
+
+    unsafe fn perfmon_program(index: u32, event: u64, value: u64, flags: u32) {
+        amd64::perfmon::x86_perfmon_program(index, event, value, flags);
+    }
+
+    unsafe fn perfmon_enable(mask: u32) {
+        amd64::perfmon::x86_perfmon_enable(mask);
+    }
+
+    fn perfmon_read(index: u32) -> u64 {
+        amd64::perfmon::x86_perfmon_read(index)
+    }
+
+    unsafe fn perfmon_ack_overflow() -> u32 {
+        amd64::perfmon::x86_perfmon_ack_overflow()
+    }
+}
+
+// ============= ArchItrace Implementation =============
+
+impl ArchItrace for Amd64Arch {
+    fn itrace_supported() -> bool {
+        amd64::itrace::x86_itrace_supported()
+    }
+
+    unsafe fn itrace_start(base: PAddr, size: usize, offset: usize, flags: u32) {
+        amd64::itrace::x86_itrace_start(base, size, offset, flags);
+    }
+
+    unsafe fn itrace_stop() -> (usize, bool) {
+        amd64::itrace::x86_itrace_stop()
+    }
 }
 
 // ============= ArchFpu Implementation =============
 
 impl ArchFpu for Amd64Arch {
-    type FpuState = amd64::debugger::X86ThreadStateVectorRegs;
+    type FpuState = amd64::fpu::X86FpuArea;
 
     unsafe fn init() {
-        // FPU is enabled by default on AMD64
-        // No special init needed
+        amd64::fpu::x86_fpu_init();
     }
 
     unsafe fn save(state: *mut Self::FpuState) {
-        // Get the current thread - for now use null
-        // In a real implementation, this would get the current thread
-        amd64::debugger::x86_get_set_vector_regs(
-            &mut crate::kernel::thread::Thread::dummy_thread(),
-            state,
-            crate::kernel::arch::amd64::debugger::RegAccess::Get,
-        );
+        amd64::fpu::x86_fpu_save(state);
     }
 
     unsafe fn restore(state: *const Self::FpuState) {
-        // Get the current thread - for now use null
-        amd64::debugger::x86_get_set_vector_regs(
-            &mut crate::kernel::thread::Thread::dummy_thread(),
-            state as *mut _,
-            crate::kernel::arch::amd64::debugger::RegAccess::Set,
-        );
+        amd64::fpu::x86_fpu_restore(state);
     }
 
     fn is_enabled() -> bool {
-        true // FPU is always enabled on x86-64
+        amd64::fpu::x86_fpu_enabled()
     }
 
     unsafe fn enable() {
-        // FPU is always enabled, nothing to do
+        amd64::fpu::x86_fpu_enable();
     }
 
     unsafe fn disable() {
-        // Can't really disable FPU on x86-64
-        // Would require CR0 modifications
+        amd64::fpu::x86_fpu_disable();
+    }
+
+    fn state_size() -> usize {
+        amd64::fpu::x86_fpu_state_size()
+    }
+
+    unsafe fn init_state(state: *mut Self::FpuState) {
+        amd64::fpu::x86_fpu_init_state(state);
     }
 }
 
@@ -82,6 +82,14 @@ pub fn apic_timer_stop() {
     // TODO: Stop APIC timer
 }
 
+/// Route performance counter overflows to `vector` through the LVT
+///
+/// The LVT entry masks itself on every delivery, so this is also called
+/// after each overflow.
+pub fn apic_pmi_unmask(_vector: u8) {
+    // TODO: Program the performance counter LVT entry
+}
+
 /// APIC error interrupt handler
 pub fn apic_error_interrupt_handler() {
     // TODO: Handle APIC errors
@@ -89,7 +97,10 @@ pub fn apic_error_interrupt_handler() {
 
 /// APIC timer interrupt handler
 pub fn apic_timer_interrupt_handler() {
-    // TODO: Handle timer interrupts
+    use crate::kernel::timer;
+
+    timer::timer_tick(timer::current_time());
+    crate::kernel::sched::timer_tick();
 }
 
 /// I/O APIC save state
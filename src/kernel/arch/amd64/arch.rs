@@ -15,8 +15,8 @@ use core::ptr;
 
 use crate::kernel::arch::amd64;
 use crate::kernel::arch::amd64::apic;
-use crate::kernel::arch::amd64::descriptor;
 use crate::kernel::arch::amd64::feature;
+use crate::kernel::arch::amd64::fpu;
 use crate::kernel::arch::amd64::mmu;
 use crate::kernel::arch::amd64::mp;
 use crate::kernel::arch::amd64::registers::*;
@@ -25,6 +25,7 @@ use crate::kernel::arch::amd64::uspace_entry;
 use crate::kernel::debug;
 use crate::println;
 use crate::kernel::thread::Thread;
+use crate::rustux::types::VAddr;
 // use crate::lk::init;  // TODO: Implement lk module
 // use crate::platform;  // TODO: Implement platform module
 
@@ -37,6 +38,9 @@ pub static mut ZBI_BASE: *mut u8 = ptr::null_mut();
 /// is fully initialized.
 pub fn arch_early_init() {
     mmu::x86_mmu_early_init();
+
+    // Descriptor tables, the boot CPU's TSS and the SYSCALL MSRs
+    unsafe { mp::x86_init_percpu(0); }
 }
 
 /// Main architecture initialization
@@ -62,9 +66,6 @@ pub fn arch_init() {
 
     mmu::x86_mmu_init();
 
-    descriptor::gdt_setup();
-    descriptor::idt_setup_readonly();
-
     // x86_processor_trace_init() - TODO: Implement processor trace support
 }
 
@@ -156,6 +157,7 @@ unsafe fn finish_secondary_entry(
     let cr0 = x86_get_cr0();
     assert!(cr0 & X86_CR0_CD != 0, "Cache disabled bit not set");
     mmu::x86_mmu_percpu_init();
+    fpu::x86_fpu_init();
 
     // Load the appropriate PAT/MTRRs. This must happen after init_percpu, so
     // that this CPU is considered online.
@@ -218,17 +220,12 @@ pub unsafe extern "C" fn x86_secondary_entry(
 
     assert!(cpu_num > 0, "Secondary CPU num must be > 0");
 
-    // Set %gs.base to our percpu struct. This has to be done before
-    // calling x86_init_percpu, which initializes most of that struct, so
-    // that x86_init_percpu can use safe-stack and/or stack-protector code.
-    // TODO: Implement percpu array for multiple CPUs
-    // For now, we just use x86_get_percpu() which gets the current CPU's percpu via GS
-    let _percpu = unsafe { mp::x86_get_percpu() };
-    // write_msr(X86_MSR_IA32_GS_BASE, percpu as *const _ as u64);
+    // Points %gs.base at our percpu struct and loads the descriptor
+    // tables; nothing before this may use %gs.
+    mp::x86_init_percpu(cpu_num);
 
     // Copy the stack-guard value from the boot CPU's percpu
-    let bp_percpu = unsafe { mp::x86_get_percpu() };
-    // (*percpu).stack_guard = (*bp_percpu).stack_guard;
+    (*mp::x86_get_percpu()).stack_guard = (*mp::bp_percpu()).stack_guard;
 
     // TODO: Set up safe stack if enabled
     // #[cfg(feature = "safe_stack")]
@@ -240,8 +237,6 @@ pub unsafe extern "C" fn x86_secondary_entry(
     //     );
     // }
 
-    mp::x86_init_percpu(cpu_num);
-
     // Now do the rest of the work, in a function that is free to use %gs in its code.
     finish_secondary_entry(&*aps_still_booting, &mut *thread, cpu_num);
 }
@@ -306,16 +301,26 @@ pub fn arch_late_init() {
 
 /// Initialize architecture-specific thread state
 ///
+/// Builds the frame the first switch to the thread pops, which returns
+/// into [`x86_thread_trampoline`](amd64::asm::x86_thread_trampoline) to
+/// call the thread's entry point with its argument.
+///
 /// # Arguments
 ///
 /// * `thread` - Thread to initialize
+/// * `stack_top` - Top of the thread's kernel stack
 ///
 /// # Safety
 ///
-/// thread must point to valid memory
-pub unsafe fn arch_thread_initialize(thread: *mut Thread) {
-    // TODO: Initialize arch-specific thread state
-    let _ = thread;
+/// thread must point to valid memory and nothing else may use the stack
+pub unsafe fn arch_thread_initialize(thread: *mut Thread, stack_top: VAddr) {
+    // The trampoline starts with the stack 16-byte aligned
+    let frame = ((stack_top & !0xf) as *mut amd64::asm::X86ContextSwitchFrame).offset(-1);
+    ptr::write_bytes(frame, 0, 1);
+    (*frame).r12 = (*thread).entry_point as u64;
+    (*frame).r13 = (*thread).entry_arg as u64;
+    (*frame).rip = amd64::asm::x86_thread_trampoline as usize as u64;
+    (*thread).arch.sp = frame as VAddr;
 }
 
 /// Context switch to a new thread
@@ -329,9 +334,30 @@ pub unsafe fn arch_thread_initialize(thread: *mut Thread) {
 ///
 /// Both pointers must be valid and this must only be called from proper context
 pub unsafe fn arch_context_switch(old_thread: *mut Thread, new_thread: *mut Thread) {
-    // TODO: Implement context switch
-    let _ = old_thread;
-    let _ = new_thread;
+    // The compiler reads the stack guard from %gs:ZX_TLS_STACK_GUARD_OFFSET,
+    // so the per-CPU slot has to follow the running thread.
+    crate::kernel::arch::amd64::include::arch::current_thread::x86_write_gs_offset64(
+        crate::rustux::tls::ZX_TLS_STACK_GUARD_OFFSET as u32,
+        (*new_thread).arch.stack_guard,
+    );
+
+    // User threads get the I/O ports their process was granted
+    let process = (*new_thread).pid().and_then(crate::kernel::process::lookup);
+    match process {
+        Some(process) => amd64::ioport::x86_load_io_bitmap(process.io_bitmap.lock().as_deref()),
+        None => amd64::ioport::x86_load_io_bitmap(None),
+    }
+
+    // Entries from user mode land on the new thread's kernel stack
+    mmu::x86_set_tss_sp((*new_thread).stack_top() as u64);
+
+    // Save extended register state if it was used, see thread::fpu
+    crate::kernel::thread::fpu::switch_out(&*old_thread);
+
+    amd64::asm::x86_64_context_switch(
+        ptr::addr_of_mut!((*old_thread).arch.sp).cast(),
+        (*new_thread).arch.sp as u64,
+    );
 }
 
 /// Check if an address is in user space
@@ -22,15 +22,18 @@ const PAGE_SIZE: usize = 4096;
 
 /// Context switch frame structure
 ///
-/// This must match the layout expected by x86_64_context_switch
+/// This must match the layout expected by x86_64_context_switch: the
+/// callee-saved registers in the order it pops them, then the address it
+/// returns to.
 #[repr(C)]
 pub struct X86ContextSwitchFrame {
-    rbx: u64,
-    rbp: u64,
-    r12: u64,
-    r13: u64,
-    r14: u64,
-    r15: u64,
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub rip: u64,
 }
 
 /// Perform a context switch between threads
@@ -71,6 +74,19 @@ pub unsafe extern "C" fn x86_64_context_switch(oldsp: *mut u64, newsp: u64) {
     );
 }
 
+/// First code a new thread runs, returned to by its first context switch
+///
+/// Calls the entry point in r12 with the argument in r13; the stack is
+/// 16-byte aligned here, as for a call. Entry points never return.
+#[unsafe(naked)]
+pub unsafe extern "C" fn x86_thread_trampoline() {
+    naked_asm!(
+        "mov rdi, r13",
+        "call r12",
+        "ud2"
+    );
+}
+
 /// Acquire a spin lock
 ///
 /// Uses the current CPU number + 1 as the lock value.
@@ -11,10 +11,12 @@
 //! vector registers (SSE, AVX, etc.).
 
 
+use crate::kernel::arch::amd64::fpu;
 use crate::kernel::arch::amd64::registers::*;
 use crate::kernel::arch::amd64::X86Iframe;
-use crate::kernel::thread::Thread;
+use crate::kernel::thread::{self, Thread};
 use crate::rustux::types::*;
+use crate::rustux::types::err::*;
 use core::mem;
 
 /// User-accessible flags in RFLAGS
@@ -128,11 +130,10 @@ pub unsafe fn x86_fill_in_iframe_from_gregs(
 }
 
 /// XSAVE state indices
-const X86_XSAVE_STATE_INDEX_SSE: u32 = 0;
-const X86_XSAVE_STATE_INDEX_AVX: u32 = 2;
-const X86_XSAVE_STATE_INDEX_MPX: u32 = 3;  // Memory Protection Keys
-const X86_XSAVE_STATE_INDEX_AVX512: u32 = 5;  // AVX-512
-const X86_XSAVE_STATE_INDEX_PKRU: u32 = 9;   // PKRU register
+const X86_XSAVE_STATE_INDEX_SSE: u32 = 1;        // XMM0-15
+const X86_XSAVE_STATE_INDEX_AVX: u32 = 2;        // Upper halves of YMM0-15
+const X86_XSAVE_STATE_INDEX_ZMM_HI256: u32 = 6;  // Upper halves of ZMM0-15
+const X86_XSAVE_STATE_INDEX_HI16_ZMM: u32 = 7;   // ZMM16-31
 
 /// Extended register state component for SSE (legacy area)
 #[repr(C)]
@@ -169,9 +170,81 @@ pub unsafe fn x86_get_set_vector_regs(
     regs: *mut X86ThreadStateVectorRegs,
     access: RegAccess,
 ) -> i32 {
-    // TODO: Implement full x86_get_set_vector_regs
-    // For now, return success as a stub
-    0
+    let set = matches!(access, RegAccess::Set);
+    let regs = &mut *regs;
+
+    let done = thread::fpu::with_state(thread, set, |area| {
+        let base = area as *mut u8;
+        let mxcsr = base.add(fpu::FXSAVE_MXCSR_OFFSET) as *mut u32;
+        if set {
+            mxcsr.write_unaligned(regs.mxcsr);
+        } else {
+            regs.mxcsr = mxcsr.read_unaligned();
+        }
+
+        // (component, first register, register count, bytes per register
+        // in the component, offset of those bytes in each ZMM)
+        let parts = [
+            (X86_XSAVE_STATE_INDEX_SSE, 0, 16, 16, 0),
+            (X86_XSAVE_STATE_INDEX_AVX, 0, 16, 16, 16),
+            (X86_XSAVE_STATE_INDEX_ZMM_HI256, 0, 16, 32, 32),
+            (X86_XSAVE_STATE_INDEX_HI16_ZMM, 16, 16, 64, 0),
+        ];
+        for (component, first, count, len, at) in parts {
+            transfer_component(area, component, &mut regs.zmm[first..first + count], len, at, set);
+        }
+    });
+
+    match done {
+        Some(()) => RX_OK,
+        None => RX_ERR_NO_MEMORY,
+    }
+}
+
+/// Copy one XSAVE component between a save area and `zmm`
+///
+/// Each register has `len` bytes in the component, which go at byte `at`
+/// of its ZMM. Components the CPU doesn't save read as zero and ignore
+/// writes; components in their initial state read as zero.
+unsafe fn transfer_component(
+    area: *mut fpu::X86FpuArea,
+    component: u32,
+    zmm: &mut [X86Zmm],
+    len: usize,
+    at: usize,
+    set: bool,
+) {
+    let offset = match fpu::x86_fpu_component_offset(component) {
+        Some(offset) if fpu::x86_fpu_xsave_enabled() || component == X86_XSAVE_STATE_INDEX_SSE => offset,
+        _ => {
+            if !set {
+                zmm.iter_mut().for_each(|reg| reg.data[at..at + len].fill(0));
+            }
+            return;
+        }
+    };
+
+    let bit = 1u64 << component;
+    let xstate_bv = fpu::x86_fpu_xsave_enabled().then(|| fpu::x86_fpu_xstate_bv(area));
+    let present = xstate_bv.map_or(true, |bv| *bv & bit != 0);
+
+    let base = (area as *mut u8).add(offset);
+    for (i, reg) in zmm.iter_mut().enumerate() {
+        let saved = core::slice::from_raw_parts_mut(base.add(i * len), len);
+        if set {
+            saved.copy_from_slice(&reg.data[at..at + len]);
+        } else if present {
+            reg.data[at..at + len].copy_from_slice(saved);
+        } else {
+            reg.data[at..at + len].fill(0);
+        }
+    }
+
+    if set {
+        if let Some(bv) = xstate_bv {
+            *bv |= bit;
+        }
+    }
 }
 
 /// Register access direction
@@ -227,13 +300,6 @@ pub unsafe fn x86_disable_debug_state() {
 
 // External register access functions
 extern "C" {
-    fn x86_get_extended_register_state_component(
-        state: *mut core::ffi::c_void,
-        index: u32,
-        get: bool,
-        size_out: *mut u32,
-    ) -> *mut core::ffi::c_void;
-
     fn x86_get_dr6() -> u64;
     fn x86_set_dr(index: u32, value: u64);
     fn x86_set_dr7(value: u64);
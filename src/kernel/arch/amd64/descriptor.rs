@@ -6,10 +6,35 @@
 
 //! x86 Descriptor Tables
 //!
-//! This module provides GDT and IDT setup functions.
-
+//! This module provides GDT, TSS and IDT setup functions.
+//!
+//! # GDT Layout
+//!
+//! SYSCALL and SYSRET take their selectors from IA32_STAR rather than the
+//! GDT, which fixes the order of the first entries: SYSCALL loads the
+//! kernel code selector and the entry after it, and SYSRET to 64-bit mode
+//! loads the two entries after the (unused) 32-bit user code selector.
+//! Each CPU's TSS follows, two entries apiece.
+//!
+//! | Index | Selector | Segment              |
+//! |-------|----------|----------------------|
+//! | 1     | 0x08     | Kernel code          |
+//! | 2     | 0x10     | Kernel data          |
+//! | 3     | 0x1b     | User code, 32-bit    |
+//! | 4     | 0x23     | User data            |
+//! | 5     | 0x2b     | User code, 64-bit    |
+//! | 6 + 2n| 0x30+16n | TSS of CPU n         |
+//!
+//! # Interrupt Stacks
+//!
+//! NMIs, double faults and machine checks switch to a stack of their own
+//! from the TSS's interrupt stack table. They can arrive with the kernel
+//! stack overflowed, or in the middle of a stack switch, where the
+//! interrupted stack cannot be trusted.
 
-use crate::rustux::types::*;
+use crate::kernel::arch::amd64::ioport::IO_BITMAP_BYTES;
+use crate::kernel::arch::amd64::mp::PerCpu;
+use crate::kernel::percpu::SMP_MAX_CPUS;
 
 // ============================================================================
 // GDT (Global Descriptor Table) Structures
@@ -52,7 +77,8 @@ pub struct TaskStateSegment {
     pub ist5: u64,     // Interrupt Stack Table 5
     pub ist6: u64,     // Interrupt Stack Table 6
     pub ist7: u64,     // Interrupt Stack Table 7
-    pub reserved3: u16,
+    pub reserved3: u64,
+    pub reserved4: u16,
     pub iomap_base: u16, // I/O map base address
 }
 
@@ -60,11 +86,23 @@ pub struct TaskStateSegment {
 pub const GDT_NULL: usize = 0;
 pub const GDT_KERNEL_CODE: usize = 1;
 pub const GDT_KERNEL_DATA: usize = 2;
-pub const GDT_USER_CODE: usize = 3;
+pub const GDT_USER_CODE_32: usize = 3;
 pub const GDT_USER_DATA: usize = 4;
-pub const GDT_TSS_LOW: usize = 5;
-pub const GDT_TSS_HIGH: usize = 6;
-pub const GDT_ENTRIES: usize = 7;
+pub const GDT_USER_CODE_64: usize = 5;
+pub const GDT_TSS_BASE: usize = 6;
+pub const GDT_ENTRIES: usize = GDT_TSS_BASE + 2 * SMP_MAX_CPUS;
+
+/// Segment selectors
+pub const KERNEL_CODE_SELECTOR: u16 = (GDT_KERNEL_CODE * 8) as u16;
+pub const KERNEL_DATA_SELECTOR: u16 = (GDT_KERNEL_DATA * 8) as u16;
+pub const USER_CODE_32_SELECTOR: u16 = (GDT_USER_CODE_32 * 8) as u16 | 3;
+pub const USER_DATA_SELECTOR: u16 = (GDT_USER_DATA * 8) as u16 | 3;
+pub const USER_CODE_64_SELECTOR: u16 = (GDT_USER_CODE_64 * 8) as u16 | 3;
+
+/// Selector of CPU `cpu_num`'s TSS
+pub const fn TSS_SELECTOR(cpu_num: u32) -> u16 {
+    ((GDT_TSS_BASE + 2 * cpu_num as usize) * 8) as u16
+}
 
 // Access byte flags
 pub const ACC_PRESENT: u8 = 0x80;
@@ -74,15 +112,15 @@ pub const ACC_CODE: u8 = 0x0A;
 pub const ACC_DATA: u8 = 0x02;
 pub const ACC_DPL0: u8 = 0x00;
 pub const ACC_DPL3: u8 = 0x60;
+pub const ACC_TSS_AVAILABLE: u8 = 0x09;
 
 // Flags byte flags
 pub const FLAG_GRANULARITY_4K: u8 = 0x80;
 pub const FLAG_SIZE_64BIT: u8 = 0x20;
 
-// Global GDT storage
+// Global GDT storage, shared by all CPUs
 static mut GDT: [GdtEntry; GDT_ENTRIES] = [GdtEntry::null(); GDT_ENTRIES];
 static mut GDT_POINTER: GdtPointer = GdtPointer { limit: 0, base: 0 };
-static mut TSS: TaskStateSegment = TaskStateSegment::null();
 
 impl GdtEntry {
     pub const fn null() -> Self {
@@ -118,14 +156,16 @@ impl GdtEntry {
         }
     }
 
+    /// Second half of a TSS descriptor: bits 63:32 of the base, then
+    /// reserved bytes
     pub fn set_tss_high(base: u64) -> Self {
         Self {
             limit_low: ((base >> 32) & 0xFFFF) as u16,
-            base_low: 0,
+            base_low: ((base >> 48) & 0xFFFF) as u16,
             base_mid: 0,
             access: 0,
-            flags_limit_high: ((base >> 48) as u8) & 0xFF,
-            base_high: ((base >> 56) as u8) & 0xFF,
+            flags_limit_high: 0,
+            base_high: 0,
         }
     }
 }
@@ -147,12 +187,39 @@ impl TaskStateSegment {
             ist6: 0,
             ist7: 0,
             reserved3: 0,
+            reserved4: 0,
             iomap_base: 0,
         }
     }
+
+    /// Set IST slot `ist` (1-7) to `sp`
+    pub fn set_ist(&mut self, ist: u8, sp: u64) {
+        match ist {
+            1 => self.ist1 = sp,
+            2 => self.ist2 = sp,
+            3 => self.ist3 = sp,
+            4 => self.ist4 = sp,
+            5 => self.ist5 = sp,
+            6 => self.ist6 = sp,
+            7 => self.ist7 = sp,
+            _ => panic!("invalid IST slot {}", ist),
+        }
+    }
+}
+
+/// IA32_STAR value for the GDT layout
+///
+/// Bits 47:32 are the SYSCALL CS (SS is the next entry); bits 63:48 are
+/// the base SYSRET adds 16 to for the 64-bit CS and 8 to for SS.
+pub const fn x86_star_value() -> u64 {
+    ((USER_CODE_32_SELECTOR as u64) << 48) | ((KERNEL_CODE_SELECTOR as u64) << 32)
 }
 
 /// Setup the GDT (Global Descriptor Table)
+///
+/// Fills in the code and data segments. TSS descriptors are added by each
+/// CPU in [`x86_initialize_percpu_tss`], and every CPU loads the table
+/// with [`x86_load_gdt`].
 pub fn gdt_setup() {
     unsafe {
         // Null descriptor (required)
@@ -174,13 +241,9 @@ pub fn gdt_setup() {
             FLAG_GRANULARITY_4K,                                      // 4KB pages
         );
 
-        // User code segment (64-bit)
-        GDT[GDT_USER_CODE] = GdtEntry::set_gate(
-            0,                      // Base (ignored in long mode)
-            0xFFFFF,                // Limit (ignored in long mode)
-            ACC_PRESENT | ACC_CODE_DATA | ACC_CODE | ACC_DPL3, // Present, Code, DPL3
-            FLAG_GRANULARITY_4K | FLAG_SIZE_64BIT,               // 4KB pages, 64-bit
-        );
+        // 32-bit user code segment: only a placeholder for the STAR layout,
+        // left not present so compatibility mode cannot be entered
+        GDT[GDT_USER_CODE_32] = GdtEntry::null();
 
         // User data segment
         GDT[GDT_USER_DATA] = GdtEntry::set_gate(
@@ -190,29 +253,118 @@ pub fn gdt_setup() {
             FLAG_GRANULARITY_4K,                                      // 4KB pages
         );
 
-        // TSS entry (needs two entries)
-        let tss_base = &TSS as *const TaskStateSegment as u64;
-        let tss_limit = core::mem::size_of::<TaskStateSegment>() as u32;
-        let tss_access = ACC_PRESENT | 0x09; // Present, TSS, DPL0
-
-        GDT[GDT_TSS_LOW] = GdtEntry::set_tss_low(tss_base, tss_limit, tss_access);
-        GDT[GDT_TSS_HIGH] = GdtEntry::set_tss_high(tss_base);
+        // User code segment (64-bit)
+        GDT[GDT_USER_CODE_64] = GdtEntry::set_gate(
+            0,                      // Base (ignored in long mode)
+            0xFFFFF,                // Limit (ignored in long mode)
+            ACC_PRESENT | ACC_CODE_DATA | ACC_CODE | ACC_DPL3, // Present, Code, DPL3
+            FLAG_GRANULARITY_4K | FLAG_SIZE_64BIT,               // 4KB pages, 64-bit
+        );
 
         // Setup GDT pointer
         GDT_POINTER.limit = ((core::mem::size_of::<GdtEntry>() * GDT_ENTRIES) - 1) as u16;
         GDT_POINTER.base = &GDT as *const GdtEntry as u64;
+    }
+}
+
+/// Load the GDT on the current CPU and reload the segment registers
+///
+/// CS and SS are reloaded because the GDT the CPU booted with may have put
+/// the kernel segments elsewhere. FS and GS are left alone: loading them
+/// would clear their bases, and GS holds the per-CPU pointer.
+///
+/// # Safety
+///
+/// [`gdt_setup`] must have run.
+pub unsafe fn x86_load_gdt() {
+    gdt_load(&GDT_POINTER);
+
+    core::arch::asm!(
+        "push {cs}",
+        "lea {tmp}, [rip + 2f]",
+        "push {tmp}",
+        "retfq",
+        "2:",
+        "mov ss, {ss:e}",
+        "mov ds, {null:e}",
+        "mov es, {null:e}",
+        cs = const KERNEL_CODE_SELECTOR as u64,
+        tmp = out(reg) _,
+        ss = in(reg) KERNEL_DATA_SELECTOR as u32,
+        null = in(reg) 0u32,
+        options(preserves_flags)
+    );
+}
 
-        // Load GDT
-        gdt_load(&GDT_POINTER);
+/// Set up `percpu`'s TSS and load it on the current CPU
+///
+/// Points the interrupt stack table at the CPU's interrupt stacks and
+/// writes its TSS descriptor, which also clears the busy bit a previous
+/// `ltr` left there (as on resume). RSP0 is kept.
+///
+/// # Safety
+///
+/// Must run on CPU `cpu_num`, after [`x86_load_gdt`].
+pub unsafe fn x86_initialize_percpu_tss(percpu: &mut PerCpu, cpu_num: u32) {
+    let rsp0 = percpu.default_tss.rsp0;
+    let nmi = percpu.interrupt_stack_top(IST_NMI);
+    let double_fault = percpu.interrupt_stack_top(IST_DOUBLE_FAULT);
+    let machine_check = percpu.interrupt_stack_top(IST_MACHINE_CHECK);
+
+    // Every port is denied until a process that was granted some runs
+    percpu.io_bitmap.fill(0xFF);
+    percpu.io_bitmap_generation = 0;
+
+    let tss = &mut percpu.default_tss;
+    *tss = TaskStateSegment::null();
+    tss.rsp0 = rsp0;
+    tss.set_ist(IST_NMI, nmi);
+    tss.set_ist(IST_DOUBLE_FAULT, double_fault);
+    tss.set_ist(IST_MACHINE_CHECK, machine_check);
+    // The I/O permission bitmap follows the TSS in the per-CPU structure
+    tss.iomap_base = core::mem::size_of::<TaskStateSegment>() as u16;
+
+    let tss_base = tss as *const TaskStateSegment as u64;
+    let tss_limit = (core::mem::size_of::<TaskStateSegment>() + IO_BITMAP_BYTES) as u32;
+    let index = GDT_TSS_BASE + 2 * cpu_num as usize;
+    GDT[index] = GdtEntry::set_tss_low(tss_base, tss_limit, ACC_PRESENT | ACC_TSS_AVAILABLE);
+    GDT[index + 1] = GdtEntry::set_tss_high(tss_base);
+
+    tss_load(TSS_SELECTOR(cpu_num));
+}
 
-        // Load TSS
-        let tss_selector = (GDT_TSS_LOW * 8) as u16;
-        tss_load(tss_selector);
-    }
+/// Load the current CPU's TSS again
+///
+/// A VM exit leaves TR with the minimum TSS limit, which cuts off the I/O
+/// permission bitmap; loading the selector again reads the full limit
+/// from the GDT. The busy bit the first `ltr` set is cleared first, since
+/// `ltr` faults on a busy TSS.
+///
+/// # Safety
+///
+/// Must run on CPU `cpu_num` with interrupts disabled, after
+/// [`x86_initialize_percpu_tss`].
+pub unsafe fn x86_reload_tss(cpu_num: u32) {
+    let index = GDT_TSS_BASE + 2 * cpu_num as usize;
+    GDT[index].access = ACC_PRESENT | ACC_TSS_AVAILABLE;
+    tss_load(TSS_SELECTOR(cpu_num));
+}
+
+/// Set the stack the current CPU switches to on entry from user mode
+///
+/// # Safety
+///
+/// The per-CPU structure must be set up.
+pub unsafe fn x86_set_tss_sp(sp: u64) {
+    (*crate::kernel::arch::amd64::mp::x86_get_percpu()).default_tss.rsp0 = sp;
 }
 
 /// Setup the IDT (Interrupt Descriptor Table)
-pub fn idt_setup_readonly() {
+///
+/// Every vector points at its stub in `_isr_table` (exceptions.S), through
+/// the gate [`gate_for_vector`] gives it. The table is shared by all CPUs;
+/// each loads it with [`x86_load_idt`].
+pub fn idt_setup() {
     unsafe {
         // External reference to the ISR table defined in exceptions.S
         extern "C" {
@@ -220,33 +372,26 @@ pub fn idt_setup_readonly() {
             static ISR_TABLE: [*const (); 256];
         }
 
-        // Code segment selector for kernel (index 1 * 8 = 8, for ring 0)
-        const KERNEL_CS: u16 = GDT_KERNEL_CODE as u16 * 8;
-
         // Populate IDT entries
         for i in 0..IDT_ENTRIES {
-            let isr_ptr = ISR_TABLE[i] as u64;
-
-            IDT[i] = IdtEntry {
-                offset_low: (isr_ptr & 0xFFFF) as u16,
-                selector: KERNEL_CS,
-                ist: 0,  // IST0 (no special stack)
-                type_attr: IDT_INTERRUPT_GATE,
-                offset_mid: ((isr_ptr >> 16) & 0xFFFF) as u16,
-                offset_high: ((isr_ptr >> 32) & 0xFFFFFFFF) as u32,
-                reserved: 0,
-            };
+            IDT[i] = IdtEntry::new(ISR_TABLE[i] as u64, gate_for_vector(i as u8));
         }
 
         // Setup IDT pointer
         IDT_POINTER.limit = ((core::mem::size_of::<IdtEntry>() * IDT_ENTRIES) - 1) as u16;
         IDT_POINTER.base = &IDT as *const IdtEntry as u64;
-
-        // Load IDT
-        idt_load(&IDT_POINTER);
     }
 }
 
+/// Load the IDT on the current CPU
+///
+/// # Safety
+///
+/// [`idt_setup`] must have run.
+pub unsafe fn x86_load_idt() {
+    idt_load(&IDT_POINTER);
+}
+
 /// Extract the Requested Privilege Level (RPL) from a selector
 ///
 /// # Arguments
@@ -332,6 +477,68 @@ pub const X86_INT_APIC_ERROR: u8 = 0x21;
 
 pub const IDT_ENTRIES: usize = 256;
 
+/// Interrupt stack table slots, numbered as in the TSS (0 is none)
+pub const IST_NONE: u8 = 0;
+pub const IST_NMI: u8 = 1;
+pub const IST_DOUBLE_FAULT: u8 = 2;
+pub const IST_MACHINE_CHECK: u8 = 3;
+pub const NUM_ASSIGNED_IST_ENTRIES: usize = 3;
+
+/// Kind of IDT gate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GateType {
+    /// Clears IF on entry
+    Interrupt = 0x0E,
+    /// Leaves IF as it was
+    Trap = 0x0F,
+}
+
+/// How a vector enters the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gate {
+    pub gate_type: GateType,
+
+    /// Least privileged CPL allowed to raise the vector with `int n`
+    pub dpl: u8,
+
+    /// Interrupt stack table slot, `IST_NONE` to stay on the current
+    /// (or RSP0) stack
+    pub ist: u8,
+}
+
+impl Gate {
+    /// Interrupt gate only the kernel may raise
+    pub const fn kernel(ist: u8) -> Self {
+        Self { gate_type: GateType::Interrupt, dpl: 0, ist }
+    }
+
+    /// Interrupt gate user mode may raise with `int n`
+    pub const fn user() -> Self {
+        Self { gate_type: GateType::Interrupt, dpl: 3, ist: IST_NONE }
+    }
+
+    /// The type and attributes byte of the IDT entry
+    pub const fn type_attr(&self) -> u8 {
+        0x80 | ((self.dpl & 3) << 5) | self.gate_type as u8
+    }
+}
+
+/// The gate for `vector`
+///
+/// All vectors use interrupt gates so the stubs run with interrupts off.
+/// `int3` and `into` may be used from user mode; NMIs, double faults and
+/// machine checks get their own stacks.
+pub const fn gate_for_vector(vector: u8) -> Gate {
+    match vector {
+        X86_INT_NMI => Gate::kernel(IST_NMI),
+        X86_INT_DOUBLE_FAULT => Gate::kernel(IST_DOUBLE_FAULT),
+        X86_INT_MACHINE_CHECK => Gate::kernel(IST_MACHINE_CHECK),
+        X86_INT_BREAKPOINT | X86_INT_OVERFLOW => Gate::user(),
+        _ => Gate::kernel(IST_NONE),
+    }
+}
+
 // Global IDT storage
 static mut IDT: [IdtEntry; IDT_ENTRIES] = [IdtEntry::null(); IDT_ENTRIES];
 static mut IDT_POINTER: IdtPointer = IdtPointer { limit: 0, base: 0 };
@@ -360,6 +567,11 @@ impl IdtEntry {
             reserved: 0,
         }
     }
+
+    /// Entry sending a vector to kernel code at `handler` through `gate`
+    pub fn new(handler: u64, gate: Gate) -> Self {
+        Self::set_gate(handler, KERNEL_CODE_SELECTOR, gate.type_attr(), gate.ist)
+    }
 }
 
 // ============================================================================
@@ -396,11 +608,73 @@ pub unsafe fn tss_load(selector: u16) {
     core::arch::asm!("ltr {0:x}", in(reg) selector, options(nostack));
 }
 
-/// Get TSS reference for modification
+/// Get the current CPU's TSS for modification
 ///
 /// # Safety
 ///
 /// Caller must ensure TSS has been initialized
 pub unsafe fn get_tss() -> &'static mut TaskStateSegment {
-    &mut TSS
+    &mut (*crate::kernel::arch::amd64::mp::x86_get_percpu()).default_tss
+}
+
+// Compile-time checks for the hardware formats and the STAR layout
+const _: () = assert!(core::mem::size_of::<GdtEntry>() == 8);
+const _: () = assert!(core::mem::size_of::<IdtEntry>() == 16);
+const _: () = assert!(core::mem::size_of::<TaskStateSegment>() == 104);
+const _: () = assert!(GDT_ENTRIES * 8 <= 0x10000);
+const _: () = assert!(KERNEL_CODE_SELECTOR + 8 == KERNEL_DATA_SELECTOR);
+const _: () = assert!(USER_CODE_32_SELECTOR + 8 == USER_DATA_SELECTOR);
+const _: () = assert!(USER_CODE_32_SELECTOR + 16 == USER_CODE_64_SELECTOR);
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sysret_selectors() {
+        let star = x86_star_value();
+        let syscall_cs = (star >> 32) as u16;
+        let sysret_base = (star >> 48) as u16;
+
+        assert_eq!(syscall_cs, KERNEL_CODE_SELECTOR);
+        assert_eq!(syscall_cs + 8, KERNEL_DATA_SELECTOR);
+        assert_eq!(sysret_base + 16, USER_CODE_64_SELECTOR);
+        assert_eq!(sysret_base + 8, USER_DATA_SELECTOR);
+        assert_eq!(USER_CODE_64_SELECTOR, 0x2B);
+        assert_eq!(USER_DATA_SELECTOR, 0x23);
+    }
+
+    #[test]
+    fn test_tss_descriptor() {
+        let base = 0xFFFF_8000_1234_5678u64;
+        let low = GdtEntry::set_tss_low(base, 103, ACC_PRESENT | ACC_TSS_AVAILABLE);
+        let high = GdtEntry::set_tss_high(base);
+        let low: u64 = unsafe { core::mem::transmute(low) };
+        let high: u64 = unsafe { core::mem::transmute(high) };
+
+        assert_eq!(low, 0x1200_8934_5678_0067);
+        assert_eq!(high, 0xFFFF_8000);
+        assert_eq!(TSS_SELECTOR(0), 0x30);
+        assert_eq!(TSS_SELECTOR(1), 0x40);
+    }
+
+    #[test]
+    fn test_gates() {
+        assert_eq!(gate_for_vector(X86_INT_PAGE_FAULT).type_attr(), IDT_INTERRUPT_GATE);
+        assert_eq!(gate_for_vector(X86_INT_PAGE_FAULT).ist, IST_NONE);
+        assert_eq!(gate_for_vector(X86_INT_BREAKPOINT).type_attr(), 0xEE);
+        assert_eq!(gate_for_vector(X86_INT_NMI).ist, IST_NMI);
+        assert_eq!(gate_for_vector(X86_INT_DOUBLE_FAULT).ist, IST_DOUBLE_FAULT);
+        assert_eq!(gate_for_vector(X86_INT_MACHINE_CHECK).ist, IST_MACHINE_CHECK);
+
+        let entry = IdtEntry::new(0xFFFF_FFFF_8010_2030, gate_for_vector(X86_INT_NMI));
+        assert_eq!({ entry.offset_low }, 0x2030);
+        assert_eq!({ entry.offset_mid }, 0x8010);
+        assert_eq!({ entry.offset_high }, 0xFFFF_FFFF);
+        assert_eq!({ entry.selector }, KERNEL_CODE_SELECTOR);
+    }
 }
@@ -20,12 +20,19 @@ use crate::kernel::arch::amd64::mmu;
 use crate::kernel::arch::amd64::registers::*;
 use crate::kernel::arch::amd64::X86Iframe;
 use crate::kernel::debug;
-use crate::print;
+use crate::kernel::irq;
+use crate::kernel::lib::gdbstub;
+use crate::kernel::lib::ktrace;
+use crate::kernel::lib::perfmon;
+use crate::kernel::lib::profiler;
+use crate::kernel::object::exception::{self, GeneralRegs};
 use crate::println;
 use crate::kernel::thread;
 use crate::rustux::types::*;
 use alloc::sync::Arc;
 
+use crate::KCOUNTER;
+
 /// Page fault error code flags
 pub const PFEX_P: u64 = 1 << 0;   // Page present
 pub const PFEX_W: u64 = 1 << 1;   // Write access
@@ -42,6 +49,7 @@ pub const X86_INT_BREAKPOINT: u64 = 3;
 pub const X86_INT_INVALID_OP: u64 = 6;
 pub const X86_INT_DEVICE_NA: u64 = 7;
 pub const X86_INT_DOUBLE_FAULT: u64 = 8;
+pub const X86_INT_MACHINE_CHECK: u64 = 18;
 pub const X86_INT_FPU_FP_ERROR: u64 = 16;
 pub const X86_INT_SIMD_FP_ERROR: u64 = 19;
 pub const X86_INT_GP_FAULT: u64 = 13;
@@ -49,6 +57,7 @@ pub const X86_INT_PAGE_FAULT: u64 = 14;
 pub const X86_INT_APIC_SPURIOUS: u64 = 255;
 pub const X86_INT_APIC_ERROR: u64 = 0xfe;
 pub const X86_INT_APIC_TIMER: u64 = 0xfd;
+pub const X86_INT_APIC_PMI: u64 = 0xfc;
 
 /// Exception types for user-space dispatch
 pub const ZX_EXCP_HW_BREAKPOINT: u32 = 0x1;
@@ -63,13 +72,18 @@ pub const VMM_PF_FLAG_USER: u32 = 1 << 1;
 pub const VMM_PF_FLAG_INSTRUCTION: u32 = 1 << 2;
 pub const VMM_PF_FLAG_NOT_PRESENT: u32 = 1 << 3;
 
+KCOUNTER!(EXCEPTIONS_PAGE, "kernel.exceptions.page_fault");
+KCOUNTER!(EXCEPTIONS_BRKPT, "kernel.exceptions.breakpoint");
+KCOUNTER!(EXCEPTIONS_USER, "kernel.exceptions.user");
+KCOUNTER!(EXCEPTIONS_FPU, "kernel.exceptions.fpu");
+
 /// Check if the exception came from user mode
 fn is_from_user(frame: &X86Iframe) -> bool {
     descriptor::SELECTOR_PL(frame.user_cs as u16) != 0
 }
 
 /// Dump the fault frame for debugging
-fn dump_fault_frame(frame: &X86Iframe) {
+pub(crate) fn dump_fault_frame(frame: &X86Iframe) {
     let cr2 = unsafe { x86_get_cr2() };
 
     println!(
@@ -92,7 +106,7 @@ fn dump_fault_frame(frame: &X86Iframe) {
         " R12: {:#18x} R13: {:#18x} R14: {:#18x} R15: {:#18x}",
         frame.r12, frame.r13, frame.r14, frame.r15
     );
-    println!("errc: {:#18x}", 0); // TODO: error code needs to be passed as parameter
+    println!("errc: {:#18x}", frame.err_code);
 }
 
 /// Dump page fault error information
@@ -153,6 +167,8 @@ fn x86_fatal_pfe_handler(frame: &X86Iframe, cr2: u64, err_code: u64) -> ! {
 /// Page fault handler
 fn x86_pfe_handler(frame: &mut X86Iframe, error_code: u64) -> core::result::Result<(), i32> {
     let va = unsafe { x86_get_cr2() } as usize;
+    EXCEPTIONS_PAGE.add(1);
+    ktrace::write(ktrace::TAG_PAGE_FAULT, 0, va as u64, error_code);
 
     // TODO: Re-enable interrupts, manage preemption
     // thread_preempt_reenable_no_resched();
@@ -191,6 +207,15 @@ fn x86_pfe_handler(frame: &mut X86Iframe, error_code: u64) -> core::result::Resu
         return Err(-2); // ZX_ERR_ACCESS_DENIED
     }
 
+    // Instruction fetches from user pages in kernel mode are never legitimate
+    if supervisor_access && error_code & PFEX_I != 0 && user_addr {
+        println!(
+            "x86_pfe_handler: SMEP violation, supervisor fetch from user address {:#x}",
+            va
+        );
+        return Err(-2); // ZX_ERR_ACCESS_DENIED
+    }
+
     // Convert PF error codes to page fault flags
     let mut flags = 0u32;
     if error_code & PFEX_W != 0 {
@@ -222,9 +247,10 @@ fn x86_pfe_handler(frame: &mut X86Iframe, error_code: u64) -> core::result::Resu
 
     // Let high level code deal with user space faults
     if is_from_user(frame) {
-        // TODO: Dispatch user exception
-        // kcounter_add(exceptions_user, 1);
-        // return call_dispatch_user_exception(ZX_EXCP_FATAL_PAGE_FAULT, frame);
+        EXCEPTIONS_USER.add(1);
+        if try_dispatch_user_exception(frame, ZX_EXCP_FATAL_PAGE_FAULT) {
+            return Ok(());
+        }
     }
 
     // Fall through to fatal path
@@ -232,18 +258,42 @@ fn x86_pfe_handler(frame: &mut X86Iframe, error_code: u64) -> core::result::Resu
 }
 
 /// Debug exception handler
+///
+/// DR6 says what raised the #DB. The thread keeps it for
+/// `rx_thread_read_state` and DR6 is reset for the next one.
 fn x86_debug_handler(frame: &mut X86Iframe) {
-    let thread = unsafe { thread::get_current_thread() };
-
-    // Read debug status register DR6
-    if let Some(t) = thread {
-        // TODO: Access debug state properly
-        // For now, skip reading debug status as the context structure doesn't expose it
-        let _ = t;
+    let dr6 = unsafe { x86_read_dr6() };
+    unsafe { x86_write_dr6(X86_DR6_INIT) };
+
+    let mut debug_regs = [0u64; 4];
+    let mut dr7 = 0;
+    if let Some(t) = thread::get_current_thread() {
+        let arch = &t.arch as *const thread::ArchData as *mut thread::ArchData;
+        unsafe { (*arch).debug_state.dr6 = dr6 };
+        let state = &t.arch.debug_state;
+        debug_regs = [state.dr0, state.dr1, state.dr2, state.dr3];
+        dr7 = state.dr7;
     }
 
     // Try to dispatch to user-space handler
-    if try_dispatch_user_exception(frame, ZX_EXCP_HW_BREAKPOINT) {
+    if is_from_user(frame) {
+        let event = exception::DebugEvent::from_dr6(dr6, dr7);
+        if let Some(event) = event {
+            // A watchpoint's data address is in its DRn
+            let fault_addr = match event {
+                exception::DebugEvent::Watchpoint => {
+                    debug_regs[(dr6 & 0xf).trailing_zeros() as usize]
+                }
+                _ => 0,
+            };
+            if try_dispatch_debug_exception(frame, event, fault_addr, dr6) {
+                return;
+            }
+        }
+    }
+
+    // Kernel single step and hardware breakpoints belong to the debugger
+    if !is_from_user(frame) && gdbstub::handle_exception(frame, gdbstub::SIGTRAP) {
         return;
     }
 
@@ -252,7 +302,12 @@ fn x86_debug_handler(frame: &mut X86Iframe) {
 
 /// Breakpoint exception handler (INT 3)
 fn x86_breakpoint_handler(frame: &mut X86Iframe) {
-    if try_dispatch_user_exception(frame, ZX_EXCP_SW_BREAKPOINT) {
+    EXCEPTIONS_BRKPT.add(1);
+    if try_dispatch_debug_exception(frame, exception::DebugEvent::SoftwareBreakpoint, 0, 0) {
+        return;
+    }
+
+    if !is_from_user(frame) && gdbstub::handle_exception(frame, gdbstub::SIGTRAP) {
         return;
     }
 
@@ -291,12 +346,41 @@ fn x86_invop_handler(frame: &mut X86Iframe) {
     exception_die(frame, "invalid opcode, halting\n");
 }
 
+/// Device-not-available (#NM) handler
+///
+/// Raised by the first FPU or SSE instruction a thread runs after being
+/// switched in, while CR0.TS is set.
+fn x86_device_na_handler(frame: &mut X86Iframe) {
+    EXCEPTIONS_FPU.add(1);
+    if !is_from_user(frame) {
+        exception_die(frame, "invalid fpu use in kernel\n");
+    }
+
+    if let Some(current) = thread::get_current_thread() {
+        if thread::fpu::first_use(&current) {
+            return;
+        }
+    }
+
+    if try_dispatch_user_exception(frame, ZX_EXCP_GENERAL) {
+        return;
+    }
+
+    exception_die(frame, "device na fault\n");
+}
+
 /// Double fault handler
 fn x86_df_handler(frame: &X86Iframe) {
     // Do not give the user exception handler the opportunity to handle double faults
     exception_die(frame, "double fault, halting\n");
 }
 
+/// Machine check handler
+fn x86_mce_handler(frame: &X86Iframe) {
+    // Runs on its own stack; state may be corrupt, so do not try to recover
+    exception_die(frame, "machine check, halting\n");
+}
+
 /// NMI handler
 fn x86_nmi_handler(_frame: &X86Iframe) {
     // NMI handler - typically used for watchdog or hardware diagnostics
@@ -313,96 +397,82 @@ fn x86_unhandled_exception(frame: &mut X86Iframe) {
 
 /// Try to dispatch exception to user-space handler
 ///
-/// Returns true if the exception was handled by user-space
+/// Returns true if a handler took the exception; the faulting process is
+/// then dead and the current thread never returns to it.
 fn try_dispatch_user_exception(frame: &X86Iframe, kind: u32) -> bool {
     if !is_from_user(frame) {
         return false;
     }
 
-    // Create exception context for user-space dispatch
-    let context = ArchExceptionContext {
-        is_page_fault: kind == ZX_EXCP_FATAL_PAGE_FAULT,
-        frame: frame as *const X86Iframe,
-        cr2: unsafe { x86_get_cr2() },
+    let fault_addr = if kind == ZX_EXCP_FATAL_PAGE_FAULT {
+        unsafe { x86_get_cr2() }
+    } else {
+        0
     };
 
-    // TODO: Implement full user exception dispatch with proper signal handling
-    // For now, this is a placeholder that:
-    // 1. Re-enables preemption
-    // 2. Enables interrupts
-    // 3. Calls the user-space exception handler
-    // 4. Restores interrupt state
-
-    // Check if we have a signal handler for this exception
-    // This would typically involve:
-    // - Getting the current thread
-    // - Checking if there's a signal handler registered
-    // - Delivering the signal to user-space
-
-    // For now, return false to indicate we couldn't handle it
-    false
+    let regs = general_regs(frame);
+
+    // The thread blocks until its handler is done with it
+    amd64::arch::arch_enable_ints();
+    let handled = exception::dispatch(kind, &regs, fault_addr, frame.err_code);
+    amd64::arch::arch_disable_ints();
+    handled
 }
 
-/// Fatal exception handler - prints diagnostic and halts
-fn exception_die(frame: &X86Iframe, msg: &str) -> ! {
-    // Print exception information
-    println!("*** KERNEL PANIC ***");
-    println!("exception at rip={:#x}", frame.rip);
-    println!("cs={:#x} ss={:#x} rflags={:#x}",
-        frame.user_cs, frame.user_ss, frame.rflags);
-    print!("{}", msg);
-    dump_fault_frame(frame);
-
-    // TODO: Implement proper panic handling:
-    // - platform_panic_start() to notify other subsystems
-    // - Dump user stack if from user space
-    // - Save crash log to persistent storage
-    // - Call platform-specific halt
-
-    // For user exceptions, try to dump user stack
-    if is_from_user(frame) {
-        println!("User mode exception - stack trace:");
-        // TODO: Implement user-space stack unwinding
-        // - Check stack bounds
-        // - Unwind user stack frames
-        // - Print backtrace
+/// Try to dispatch a user-mode #DB or #BP to user-space handler
+///
+/// As [`try_dispatch_user_exception`], reporting `dr6`.
+fn try_dispatch_debug_exception(
+    frame: &X86Iframe,
+    event: exception::DebugEvent,
+    fault_addr: u64,
+    dr6: u64,
+) -> bool {
+    if !is_from_user(frame) {
+        return false;
     }
 
-    // Halt the system
-    loop {
-        unsafe { crate::kernel::arch::amd64::registers::x86_hlt() };
-    }
+    let regs = general_regs(frame);
+
+    amd64::arch::arch_enable_ints();
+    let handled = exception::dispatch_debug(event, &regs, fault_addr, dr6);
+    amd64::arch::arch_disable_ints();
+    handled
 }
 
-/// Get error code from the stack (pushed before iframe)
-///
-/// # Safety
-///
-/// Must be called with a valid iframe pointer from exception entry
-unsafe fn get_error_code(frame: *const X86Iframe) -> u64 {
-    // The error code is located 128 bytes after the iframe pointer
-    // (16 registers * 8 bytes each)
-    const IFRAME_SIZE: usize = 16 * 8;
-    let error_code_ptr = (frame as *const u8).add(IFRAME_SIZE) as *const u64;
-    *error_code_ptr
+/// Registers of the user thread that took the exception
+fn general_regs(frame: &X86Iframe) -> GeneralRegs {
+    let mut regs = GeneralRegs {
+        pc: frame.rip,
+        sp: frame.rsp,
+        fp: frame.rbp,
+        flags: frame.rflags,
+        regs: [0; 32],
+    };
+    let dwarf = [
+        frame.rax, frame.rdx, frame.rcx, frame.rbx, frame.rsi, frame.rdi, frame.rbp, frame.rsp,
+        frame.r8, frame.r9, frame.r10, frame.r11, frame.r12, frame.r13, frame.r14, frame.r15,
+    ];
+    regs.regs[..dwarf.len()].copy_from_slice(&dwarf);
+    regs
 }
 
-/// Get vector number from the stack (pushed before iframe)
-///
-/// # Safety
+/// Fatal exception handler - records the frame and panics
 ///
-/// Must be called with a valid iframe pointer from exception entry
-unsafe fn get_vector_number(frame: *const X86Iframe) -> u64 {
-    // The vector number is located 136 bytes after the iframe pointer
-    const VECTOR_OFFSET: usize = (16 * 8) + 8;
-    let vector_ptr = (frame as *const u8).add(VECTOR_OFFSET) as *const u64;
-    *vector_ptr
+/// The panic path dumps the frame and a backtrace of the faulting code.
+fn exception_die(frame: &X86Iframe, msg: &str) -> ! {
+    crate::kernel::lib::crashlog::set_iframe(frame as *const X86Iframe as *mut u8);
+
+    if is_from_user(frame) {
+        panic!("{} (user mode, rip {:#x})", msg.trim_end(), frame.rip);
+    }
+    panic!("{} (rip {:#x})", msg.trim_end(), frame.rip);
 }
 
 /// Main exception dispatch handler
 ///
-/// Called from assembly exception entry point
-/// The vector number is passed on the stack by the CPU before the iframe
+/// Called from interrupt_common and interrupt_paranoid (exceptions.S) with
+/// the iframe and its vector, with the kernel GS base live.
 #[no_mangle]
 pub unsafe extern "C" fn x86_exception_handler(frame: *mut X86Iframe, vector: u64) {
     let frame = &mut *frame;
@@ -424,11 +494,14 @@ pub unsafe extern "C" fn x86_exception_handler(frame: *mut X86Iframe, vector: u6
             x86_invop_handler(frame);
         }
         X86_INT_DEVICE_NA => {
-            exception_die(frame, "device na fault\n");
+            x86_device_na_handler(frame);
         }
         X86_INT_DOUBLE_FAULT => {
             x86_df_handler(frame);
         }
+        X86_INT_MACHINE_CHECK => {
+            x86_mce_handler(frame);
+        }
         X86_INT_FPU_FP_ERROR | X86_INT_SIMD_FP_ERROR => {
             x86_unhandled_exception(frame);
         }
@@ -436,12 +509,16 @@ pub unsafe extern "C" fn x86_exception_handler(frame: *mut X86Iframe, vector: u6
             x86_gpf_handler(frame);
         }
         X86_INT_PAGE_FAULT => {
-            // Get actual error code from stack (pushed by CPU for page faults)
-            let error_code = get_error_code(frame);
+            // Error code pushed by the CPU for page faults
+            let error_code = frame.err_code;
             if x86_pfe_handler(frame, error_code).is_err() {
                 x86_fatal_pfe_handler(frame, x86_get_cr2() as u64, error_code);
             }
         }
+        // Remaining exception vectors
+        0..=31 => {
+            x86_unhandled_exception(frame);
+        }
         X86_INT_APIC_SPURIOUS => {
             // Ignore spurious interrupts
         }
@@ -449,13 +526,45 @@ pub unsafe extern "C" fn x86_exception_handler(frame: *mut X86Iframe, vector: u6
             apic::apic_error_interrupt_handler();
             apic::apic_issue_eoi();
         }
+        X86_INT_APIC_PMI => {
+            irq::enter();
+            irq::record_vector(vector as u32);
+            perfmon::overflow(frame.rip, frame.rbp, is_from_user(frame));
+            apic::apic_pmi_unmask(X86_INT_APIC_PMI as u8);
+            apic::apic_issue_eoi();
+            if irq::exit() {
+                thread::thread_preempt();
+            }
+        }
         X86_INT_APIC_TIMER => {
+            irq::enter();
+            irq::record_vector(vector as u32);
+            ktrace::write(ktrace::TAG_IRQ_ENTER, vector as u16, 0, 0);
+            crate::kernel::lib::crypto::entropy::add_interrupt_jitter(vector as u32);
+            profiler::sample(frame.rip, frame.rbp, is_from_user(frame));
             apic::apic_timer_interrupt_handler();
             apic::apic_issue_eoi();
+            ktrace::write(ktrace::TAG_IRQ_EXIT, vector as u16, 0, 0);
+            if irq::exit() {
+                thread::thread_preempt();
+            }
         }
         _ => {
-            // Handle IRQs
-            crate::kernel::arch::amd64::arch::platform_irq(frame);
+            irq::enter();
+            irq::record_vector(vector as u32);
+            ktrace::write(ktrace::TAG_IRQ_ENTER, vector as u16, 0, 0);
+            crate::kernel::lib::crypto::entropy::add_interrupt_jitter(vector as u32);
+
+            // Vectors owned by userspace drivers (MSI, routed lines)
+            if crate::kernel::object::interrupt::dispatch(vector as u32) {
+                apic::apic_issue_eoi();
+            } else {
+                crate::kernel::arch::amd64::arch::platform_irq(frame);
+            }
+            ktrace::write(ktrace::TAG_IRQ_EXIT, vector as u16, 0, 0);
+            if irq::exit() {
+                thread::thread_preempt();
+            }
         }
     }
 }
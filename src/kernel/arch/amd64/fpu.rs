@@ -0,0 +1,272 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86 FPU and Extended Register State
+//!
+//! Saves and restores the x87, SSE, AVX and AVX-512 registers with XSAVE
+//! when the CPU has it, and with FXSAVE otherwise. The components to save
+//! are chosen from CPUID leaf 0xD and enabled in XCR0 on every CPU.
+//!
+//! Access is trapped with CR0.TS: while it is set, the first x87, SSE or
+//! AVX instruction raises #NM, which the fault handler passes to
+//! [`thread::fpu::first_use`](crate::kernel::thread::fpu::first_use).
+//!
+//! Save areas use the standard (non-compacted) XSAVE format, so each
+//! component is at the offset CPUID reports for it.
+
+use crate::kernel::arch::amd64::registers::cr::*;
+use crate::kernel::arch::amd64::registers::*;
+use core::arch::x86_64::{CpuidResult, __cpuid_count};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+// Import logging macros
+use crate::log_info;
+
+/// XSAVE state components
+pub const XSAVE_X87: u64 = 1 << 0;
+pub const XSAVE_SSE: u64 = 1 << 1;
+pub const XSAVE_AVX: u64 = 1 << 2;
+pub const XSAVE_OPMASK: u64 = 1 << 5;
+pub const XSAVE_ZMM_HI256: u64 = 1 << 6;
+pub const XSAVE_HI16_ZMM: u64 = 1 << 7;
+
+/// Components that make up AVX-512; enabled together or not at all
+const XSAVE_AVX512: u64 = XSAVE_OPMASK | XSAVE_ZMM_HI256 | XSAVE_HI16_ZMM;
+
+/// Components the kernel saves for threads
+const XSAVE_KERNEL_SUPPORTED: u64 = XSAVE_X87 | XSAVE_SSE | XSAVE_AVX | XSAVE_AVX512;
+
+/// Size of the FXSAVE area, and of the legacy region of an XSAVE area
+pub const FXSAVE_SIZE: usize = 512;
+
+/// Size of the XSAVE header that follows the legacy region
+pub const XSAVE_HEADER_SIZE: usize = 64;
+
+/// Offsets in the legacy region
+pub const FXSAVE_FCW_OFFSET: usize = 0;
+pub const FXSAVE_MXCSR_OFFSET: usize = 24;
+pub const FXSAVE_XMM_OFFSET: usize = 160;
+
+/// x87 control word and MXCSR after FNINIT / reset
+const X87_FCW_DEFAULT: u16 = 0x037F;
+const MXCSR_DEFAULT: u32 = 0x1F80;
+
+/// CPUID.1:ECX bits
+const CPUID_1_ECX_XSAVE: u32 = 1 << 26;
+
+/// CPUID.(0xD,1):EAX bits
+const CPUID_D_1_EAX_XSAVEOPT: u32 = 1 << 0;
+
+/// Whether threads are saved with XSAVE rather than FXSAVE
+static XSAVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether XSAVEOPT is available
+static XSAVEOPT: AtomicBool = AtomicBool::new(false);
+
+/// Components enabled in XCR0
+static XCR0: AtomicU64 = AtomicU64::new(XSAVE_X87 | XSAVE_SSE);
+
+/// Bytes of the save area
+static STATE_SIZE: AtomicUsize = AtomicUsize::new(FXSAVE_SIZE);
+
+/// Start of a thread's FXSAVE or XSAVE area
+///
+/// The XSAVE components follow the header; the whole area is
+/// [`x86_fpu_state_size`] bytes.
+#[repr(C, align(64))]
+pub struct X86FpuArea {
+    /// x87 and SSE state in FXSAVE layout
+    pub legacy: [u8; FXSAVE_SIZE],
+
+    /// XSAVE header, unused with FXSAVE
+    pub header: [u8; XSAVE_HEADER_SIZE],
+}
+
+#[inline]
+fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    unsafe { __cpuid_count(leaf, subleaf) }
+}
+
+unsafe fn xsetbv(index: u32, value: u64) {
+    core::arch::asm!(
+        "xsetbv",
+        in("ecx") index,
+        in("eax") value as u32,
+        in("edx") (value >> 32) as u32,
+        options(nomem, nostack)
+    );
+}
+
+/// Set up the FPU on the current CPU
+///
+/// Enables SSE and, if present, XSAVE with every component the kernel
+/// knows how to save, then sets CR0.TS so the first use traps. Runs on
+/// every CPU; the components are the same on all of them.
+pub fn x86_fpu_init() {
+    unsafe {
+        let mut cr0 = x86_get_cr0();
+        cr0 &= !CR0_EM;
+        cr0 |= CR0_MP | CR0_NE | CR0_TS;
+        x86_set_cr0(cr0);
+
+        let has_xsave = cpuid(1, 0).ecx & CPUID_1_ECX_XSAVE != 0;
+
+        let mut cr4 = x86_get_cr4();
+        cr4 |= CR4_OSFXSR | CR4_OSXMMEXCPT;
+        if has_xsave {
+            cr4 |= CR4_OSXSAVE;
+        }
+        x86_set_cr4(cr4);
+
+        if !has_xsave {
+            return;
+        }
+
+        let leaf = cpuid(0xD, 0);
+        let supported = ((leaf.edx as u64) << 32) | leaf.eax as u64;
+        let mut xcr0 = supported & XSAVE_KERNEL_SUPPORTED;
+        if xcr0 & XSAVE_AVX512 != XSAVE_AVX512 {
+            xcr0 &= !XSAVE_AVX512;
+        }
+        xsetbv(0, xcr0);
+
+        // EBX is the size for the components now enabled in XCR0
+        let size = cpuid(0xD, 0).ebx as usize;
+        let first = !XSAVE.swap(true, Ordering::Relaxed);
+        XSAVEOPT.store(cpuid(0xD, 1).eax & CPUID_D_1_EAX_XSAVEOPT != 0, Ordering::Relaxed);
+        XCR0.store(xcr0, Ordering::Relaxed);
+        STATE_SIZE.store(size.max(FXSAVE_SIZE + XSAVE_HEADER_SIZE), Ordering::Relaxed);
+
+        if first {
+            log_info!("FPU: XSAVE components {:#x}, {} byte save area", xcr0, size);
+        }
+    }
+}
+
+/// Whether save areas are in XSAVE format
+pub fn x86_fpu_xsave_enabled() -> bool {
+    XSAVE.load(Ordering::Relaxed)
+}
+
+/// Components saved for threads
+pub fn x86_fpu_components() -> u64 {
+    XCR0.load(Ordering::Relaxed)
+}
+
+/// Bytes of a thread's save area
+pub fn x86_fpu_state_size() -> usize {
+    STATE_SIZE.load(Ordering::Relaxed)
+}
+
+/// Offset of XSAVE component `index` in a save area, if it is saved
+pub fn x86_fpu_component_offset(index: u32) -> Option<usize> {
+    match index {
+        0 => Some(FXSAVE_FCW_OFFSET),
+        1 => Some(FXSAVE_XMM_OFFSET),
+        _ if x86_fpu_components() & (1 << index) != 0 => Some(cpuid(0xD, index).ebx as usize),
+        _ => None,
+    }
+}
+
+/// The XSTATE_BV field of an XSAVE area, telling which components hold
+/// saved values rather than their initial state
+///
+/// # Safety
+///
+/// `area` must be an XSAVE area.
+pub unsafe fn x86_fpu_xstate_bv(area: *mut X86FpuArea) -> *mut u64 {
+    (*area).header.as_mut_ptr() as *mut u64
+}
+
+/// Fill a zeroed save area with the reset state
+///
+/// With XSAVE, an all-zero header marks every component as being in its
+/// initial state; only MXCSR is always loaded from memory.
+///
+/// # Safety
+///
+/// `area` must be [`x86_fpu_state_size`] bytes.
+pub unsafe fn x86_fpu_init_state(area: *mut X86FpuArea) {
+    let legacy = (*area).legacy.as_mut_ptr();
+    (legacy.add(FXSAVE_FCW_OFFSET) as *mut u16).write_unaligned(X87_FCW_DEFAULT);
+    (legacy.add(FXSAVE_MXCSR_OFFSET) as *mut u32).write_unaligned(MXCSR_DEFAULT);
+}
+
+/// Save the current registers into `area`
+///
+/// # Safety
+///
+/// `area` must be [`x86_fpu_state_size`] bytes and 64-byte aligned, and
+/// the FPU must be enabled.
+pub unsafe fn x86_fpu_save(area: *mut X86FpuArea) {
+    if !x86_fpu_xsave_enabled() {
+        core::arch::asm!("fxsave64 [{}]", in(reg) area, options(nostack));
+        return;
+    }
+
+    let mask = x86_fpu_components();
+    if XSAVEOPT.load(Ordering::Relaxed) {
+        core::arch::asm!(
+            "xsaveopt64 [{}]",
+            in(reg) area,
+            in("eax") mask as u32,
+            in("edx") (mask >> 32) as u32,
+            options(nostack)
+        );
+    } else {
+        core::arch::asm!(
+            "xsave64 [{}]",
+            in(reg) area,
+            in("eax") mask as u32,
+            in("edx") (mask >> 32) as u32,
+            options(nostack)
+        );
+    }
+}
+
+/// Load the registers from `area`
+///
+/// # Safety
+///
+/// As for [`x86_fpu_save`].
+pub unsafe fn x86_fpu_restore(area: *const X86FpuArea) {
+    if !x86_fpu_xsave_enabled() {
+        core::arch::asm!("fxrstor64 [{}]", in(reg) area, options(nostack));
+        return;
+    }
+
+    let mask = x86_fpu_components();
+    core::arch::asm!(
+        "xrstor64 [{}]",
+        in(reg) area,
+        in("eax") mask as u32,
+        in("edx") (mask >> 32) as u32,
+        options(nostack)
+    );
+}
+
+/// Whether FPU instructions run without trapping
+pub fn x86_fpu_enabled() -> bool {
+    unsafe { x86_get_cr0() & CR0_TS == 0 }
+}
+
+/// Let FPU instructions run
+pub fn x86_fpu_enable() {
+    unsafe {
+        core::arch::asm!("clts", options(nomem, nostack));
+    }
+}
+
+/// Make the next FPU instruction raise #NM
+pub fn x86_fpu_disable() {
+    unsafe {
+        x86_set_cr0(x86_get_cr0() | CR0_TS);
+    }
+}
+
+// Compile-time checks for the save area layout
+const _: () = assert!(core::mem::size_of::<X86FpuArea>() == FXSAVE_SIZE + XSAVE_HEADER_SIZE);
+const _: () = assert!(core::mem::align_of::<X86FpuArea>() == 64);
@@ -42,7 +42,8 @@ pub const IDT_TASK_GATE: u8 = 0x85;
 pub fn idt_init() {
     unsafe {
         // Use the IDT setup from descriptor module
-        super::descriptor::idt_setup_readonly();
+        super::descriptor::idt_setup();
+        super::descriptor::x86_load_idt();
     }
 }
 
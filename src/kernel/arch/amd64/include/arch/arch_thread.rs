@@ -218,14 +218,9 @@ pub unsafe fn arch_thread_initialize(thread: &mut crate::kernel::thread::Thread,
     // Set the entry point
     (*frame_ptr).rip = entry_point as u64;
 
-    // Initialize extended register state (FPU/SSE/AVX)
+    // Extended register state (FPU/SSE/AVX) is allocated on first use,
+    // see thread::fpu
     let arch_state = &mut thread.arch;
-    // Allocate buffer for extended register state
-    let buf = (arch_state.extended_register_state as VAddr + 63) & !63;  // ROUNDUP to 64
-    arch_state.extended_register_state = buf as *mut core::ffi::c_void;
-
-    // Initialize the extended register state to a clean state
-    x86_extended_register_init_state(arch_state.extended_register_state);
 
     // Set the stack pointer
     arch_state.sp = frame_ptr as VAddr;
@@ -250,8 +245,13 @@ pub unsafe fn arch_thread_initialize(thread: &mut crate::kernel::thread::Thread,
 ///
 /// * `thread` - The first thread to construct
 pub fn arch_thread_construct_first(thread: &mut crate::kernel::thread::Thread) {
-    // The first thread doesn't need any special initialization on x86_64
-    // as the context switch code will handle it
+    // Adopt the stack guard start.S put in the per-CPU slot; functions
+    // already on this stack were instrumented against it.
+    thread.arch.stack_guard = unsafe {
+        crate::kernel::arch::amd64::include::arch::current_thread::x86_read_gs_offset64(
+            crate::rustux::tls::ZX_TLS_STACK_GUARD_OFFSET as u32,
+        )
+    };
 }
 
 /// Dump thread state for debugging
@@ -305,8 +305,9 @@ pub unsafe fn arch_context_switch(
 ) {
     use crate::kernel::arch::amd64::registers::*;
 
-    // Save extended register state (FPU/SSE/AVX)
-    x86_extended_register_context_switch(old_thread, new_thread);
+    // Save extended register state (FPU/SSE/AVX) if it was used, and set
+    // CR0.TS so the new thread traps on first use
+    crate::kernel::thread::fpu::switch_out(old_thread);
 
     // Handle debug register state
     x86_debug_state_context_switch(old_thread, new_thread);
@@ -402,11 +403,6 @@ unsafe fn x86_debug_state_context_switch(
 
 // External assembly functions
 extern "C" {
-    fn x86_extended_register_init_state(state: *mut core::ffi::c_void);
-    fn x86_extended_register_context_switch(
-        old_thread: &mut crate::kernel::thread::Thread,
-        new_thread: &mut crate::kernel::thread::Thread,
-    );
     fn x86_write_hw_debug_regs(state: &X86DebugState);
     fn x86_disable_debug_state();
     fn x86_64_context_switch(oldsp: *mut *const u64, newsp: *const u64);
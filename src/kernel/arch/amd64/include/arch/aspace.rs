@@ -36,6 +36,10 @@ pub struct X86PageTableMmu {
     use_global_mappings: bool,
 }
 
+// Safety: the table pages belong to this instance, and the address space
+// holding it serializes access through its page table lock
+unsafe impl Send for X86PageTableMmu {}
+
 impl X86PageTableMmu {
     /// Create a new MMU page table instance
     pub fn new() -> Self {
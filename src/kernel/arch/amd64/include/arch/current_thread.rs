@@ -33,7 +33,7 @@ use core::ptr::NonNull;
 #[inline]
 pub unsafe fn get_current_thread() -> &'static mut Thread {
     // Read thread pointer from GS segment
-    let thread_ptr = x86_read_gs_offset64(PERCPU_CURRENT_THREAD_OFFSET) as *mut Thread;
+    let thread_ptr = x86_read_gs_offset64(PERCPU_CURRENT_THREAD_OFFSET as u32) as *mut Thread;
     
     // SAFETY: The pointer is guaranteed to be valid as long as the GS segment
     // is properly set up, which is a requirement for using this function.
@@ -60,7 +60,7 @@ pub unsafe fn get_current_thread() -> &'static mut Thread {
 #[inline]
 pub unsafe fn set_current_thread(thread: *mut Thread) {
     // Write thread pointer to GS segment
-    x86_write_gs_offset64(PERCPU_CURRENT_THREAD_OFFSET, thread as u64);
+    x86_write_gs_offset64(PERCPU_CURRENT_THREAD_OFFSET as u32, thread as u64);
 }
 
 // Foreign function declarations for accessing the GS segment
@@ -6,21 +6,92 @@
 
 //! x86 I/O Port Management
 //!
-//! This module provides I/O port bitmap management for x86.
+//! User mode reaches an I/O port only if the port's bit in the TSS I/O
+//! permission bitmap is clear. Each process that has been granted ports
+//! keeps its own bitmap, and the context switch copies it into the
+//! CPU's TSS when a thread of that process is switched in.
 
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of I/O ports
+pub const IO_PORT_COUNT: usize = 0x10000;
+
+/// Size of an I/O permission bitmap, one bit per port
+pub const IO_BITMAP_BYTES: usize = IO_PORT_COUNT / 8;
+
+/// Source of bitmap generations, so a CPU can tell its copy is stale
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
 
 /// I/O port bitmap
 #[repr(C)]
 pub struct IoBitmap {
-    /// Bitmap data
-    pub bitmap: [u8; 0x1000],
+    /// Bitmap data; a set bit denies access to the port
+    pub bitmap: [u8; IO_BITMAP_BYTES],
+
+    /// Changes whenever access is granted, never 0
+    generation: u64,
 }
 
 impl IoBitmap {
-    /// Create a new I/O bitmap
+    /// Create a new I/O bitmap denying every port
     pub const fn new() -> Self {
         Self {
-            bitmap: [0; 0x1000],
+            bitmap: [0xFF; IO_BITMAP_BYTES],
+            generation: 0,
+        }
+    }
+
+    /// Allocate a bitmap denying every port, without building it on the
+    /// kernel stack first
+    pub fn boxed() -> Box<Self> {
+        // SAFETY: all-zero bytes are a valid IoBitmap
+        let mut bitmap = unsafe { Box::<Self>::new_zeroed().assume_init() };
+        bitmap.bitmap.fill(0xFF);
+        bitmap
+    }
+
+    /// Allow access to `len` ports from `base`
+    pub fn allow(&mut self, base: u16, len: u32) {
+        let end = (base as usize + len as usize).min(IO_PORT_COUNT);
+        for port in base as usize..end {
+            self.bitmap[port / 8] &= !(1 << (port % 8));
         }
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Generation of the current contents
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl Default for IoBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Give the current CPU the I/O permissions in `bitmap`, or deny every
+/// port if there is none
+///
+/// The copy is skipped if the CPU already holds this generation.
+///
+/// # Safety
+///
+/// The per-CPU structure must be set up, and the caller must not migrate
+/// to another CPU.
+pub unsafe fn x86_load_io_bitmap(bitmap: Option<&IoBitmap>) {
+    let percpu = &mut *crate::kernel::arch::amd64::mp::x86_get_percpu();
+    let generation = bitmap.map_or(0, IoBitmap::generation);
+    if percpu.io_bitmap_generation == generation {
+        return;
+    }
+
+    let dest = &mut percpu.io_bitmap[..IO_BITMAP_BYTES];
+    match bitmap {
+        Some(bitmap) => dest.copy_from_slice(&bitmap.bitmap),
+        None => dest.fill(0xFF),
     }
+    percpu.io_bitmap_generation = generation;
 }
@@ -0,0 +1,79 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86 Intel Processor Trace
+//!
+//! Traces branches with single-range output: the CPU writes packets to
+//! one physically contiguous, naturally aligned buffer and wraps to its
+//! start when it reaches the end, so the oldest trace is overwritten and
+//! a decoder syncs on the first PSB packet it finds. The output offset is
+//! saved when tracing stops and restored when it resumes.
+//!
+//! CPUs without Intel PT, or with only ToPA output, aren't supported.
+
+use crate::kernel::arch::amd64::registers::*;
+use crate::kernel::lib::itrace::*;
+use crate::rustux::types::PAddr;
+use core::arch::x86_64::__cpuid_count;
+
+/// CPUID.(7,0):EBX bit for Intel PT
+const CPUID_7_EBX_PT: u32 = 1 << 25;
+
+/// Intel PT capabilities leaf and its ECX bit for single-range output
+const CPUID_PT: u32 = 0x14;
+const CPUID_14_ECX_SINGLE_RANGE: u32 = 1 << 2;
+
+/// MSRs
+const IA32_RTIT_OUTPUT_BASE: u32 = 0x560;
+const IA32_RTIT_OUTPUT_MASK_PTRS: u32 = 0x561;
+const IA32_RTIT_CTL: u32 = 0x570;
+const IA32_RTIT_STATUS: u32 = 0x571;
+
+/// `IA32_RTIT_CTL` bits
+const RTIT_CTL_TRACE_EN: u64 = 1 << 0;
+const RTIT_CTL_OS: u64 = 1 << 2;
+const RTIT_CTL_USER: u64 = 1 << 3;
+const RTIT_CTL_TSC_EN: u64 = 1 << 10;
+const RTIT_CTL_BRANCH_EN: u64 = 1 << 13;
+
+/// `IA32_RTIT_STATUS` bits
+const RTIT_STATUS_ERROR: u64 = 1 << 4;
+
+pub fn x86_itrace_supported() -> bool {
+    unsafe {
+        let max = __cpuid_count(0, 0).eax;
+        if max < CPUID_PT || __cpuid_count(7, 0).ebx & CPUID_7_EBX_PT == 0 {
+            return false;
+        }
+        __cpuid_count(CPUID_PT, 0).ecx & CPUID_14_ECX_SINGLE_RANGE != 0
+    }
+}
+
+pub unsafe fn x86_itrace_start(base: PAddr, size: usize, offset: usize, flags: u32) {
+    let mut ctl = RTIT_CTL_TRACE_EN | RTIT_CTL_BRANCH_EN | RTIT_CTL_TSC_EN;
+    if flags & ITRACE_FLAG_USER != 0 {
+        ctl |= RTIT_CTL_USER;
+    }
+    if flags & ITRACE_FLAG_KERNEL != 0 {
+        ctl |= RTIT_CTL_OS;
+    }
+
+    // The output MSRs can only be written while TraceEn is clear
+    write_msr(IA32_RTIT_CTL, 0);
+    write_msr(IA32_RTIT_STATUS, 0);
+    write_msr(IA32_RTIT_OUTPUT_BASE, base as u64);
+    write_msr(IA32_RTIT_OUTPUT_MASK_PTRS, (offset as u64) << 32 | (size as u64 - 1));
+    write_msr(IA32_RTIT_CTL, ctl);
+}
+
+pub unsafe fn x86_itrace_stop() -> (usize, bool) {
+    // Clearing TraceEn flushes the packets in flight to memory
+    write_msr(IA32_RTIT_CTL, 0);
+    if read_msr(IA32_RTIT_STATUS) & RTIT_STATUS_ERROR != 0 {
+        crate::log_error!("itrace: output error on this CPU");
+    }
+    ((read_msr(IA32_RTIT_OUTPUT_MASK_PTRS) >> 32) as usize, false)
+}
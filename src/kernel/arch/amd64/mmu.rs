@@ -9,7 +9,11 @@
 //! This module provides page table management for x86-64.
 
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::rustux::types::*;
+use crate::kernel::arch::amd64::asm::{x86_get_cr4, x86_set_cr4, X86_CR4_SMAP, X86_CR4_SMEP};
+use crate::kernel::arch::amd64::feature;
 use crate::kernel::arch::amd64::page_tables::constants::*;
 
 // Page size constants
@@ -21,12 +25,20 @@ pub const IA32_PAT_MSR: u32 = 0x277;
 pub const IA32_MTRR_CAP_MSR: u32 = 0xFE;
 pub const IA32_MTRR_DEF_TYPE_MSR: u32 = 0x2FF;
 
-// PAT register default values (write-back caching)
-pub const PAT_DEFAULT_VALUE: u64 = 0x0007010600070106;
+// PAT register value. A PTE's PAT, PCD and PWT bits index it:
+//
+//   PA0 WB, PA1 WT, PA2 UC-, PA3 UC, PA4 WC, PA5 WT, PA6 UC-, PA7 UC
+//
+// The first four match the power-on value, so PCD and PWT alone keep their
+// architectural meaning; PA4 (the PAT bit alone) adds write-combining.
+pub const PAT_DEFAULT_VALUE: u64 = 0x0007040100070406;
 
 // Global page table state (simplified - in real kernel would be per-address space)
 static mut BOOT_PML4: Option<PAddr> = None;
 
+/// Set once SMAP has been enabled in CR4, so user copies know to STAC/CLAC
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
+
 /// Early MMU initialization
 ///
 /// This is called very early in boot to set up basic MMU state.
@@ -72,12 +84,63 @@ pub fn x86_mmu_init() {
 pub fn x86_mmu_percpu_init() {
     unsafe {
         // Initialize PAT (Page Attribute Table) for proper memory caching
-        // The default PAT value provides write-back caching for most memory
+        // Write-back for most memory, plus write-combining at PA4
         x86_write_msr(IA32_PAT_MSR, PAT_DEFAULT_VALUE);
 
         // Initialize MTRR (Memory Type Range Registers) if supported
         // For now, we use the default BIOS settings
         // TODO: Implement proper MTRR initialization
+
+        // Keep the kernel from executing or touching user pages by accident
+        x86_enable_user_protections();
+    }
+}
+
+/// Enable SMEP and SMAP on this CPU if the processor supports them
+///
+/// With SMEP set, a supervisor-mode instruction fetch from a user page
+/// faults. With SMAP set, supervisor data accesses to user pages fault
+/// unless RFLAGS.AC is set, which only happens inside
+/// [`x86_user_access_begin`]/[`x86_user_access_end`].
+unsafe fn x86_enable_user_protections() {
+    let mut cr4 = x86_get_cr4();
+
+    if feature::x86_feature_smep() {
+        cr4 |= X86_CR4_SMEP;
+    }
+    if feature::x86_feature_smap() {
+        cr4 |= X86_CR4_SMAP;
+        SMAP_ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    x86_set_cr4(cr4);
+}
+
+/// Check whether SMAP is enforced
+#[inline]
+pub fn x86_smap_enabled() -> bool {
+    SMAP_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Check whether SMEP is enforced on this CPU
+#[inline]
+pub fn x86_smep_enabled() -> bool {
+    unsafe { x86_get_cr4() & X86_CR4_SMEP != 0 }
+}
+
+/// Open a window in which the kernel may access user pages (STAC)
+#[inline]
+pub fn x86_user_access_begin() {
+    if x86_smap_enabled() {
+        unsafe { core::arch::asm!("stac", options(nomem, nostack)) };
+    }
+}
+
+/// Close the user access window opened by [`x86_user_access_begin`] (CLAC)
+#[inline]
+pub fn x86_user_access_end() {
+    if x86_smap_enabled() {
+        unsafe { core::arch::asm!("clac", options(nomem, nostack)) };
     }
 }
 
@@ -240,9 +303,7 @@ pub unsafe fn x86_write_msr(msr: u32, value: u64) {
 /// This modifies critical task state segment data.
 #[inline]
 pub unsafe fn x86_set_tss_sp(sp: u64) {
-    // TODO: Implement TSS SP0 setting
-    // This requires access to the TSS structure
-    let _ = sp;
+    crate::kernel::arch::amd64::descriptor::x86_set_tss_sp(sp);
 }
 
 /// Set DS segment register
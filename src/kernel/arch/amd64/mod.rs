@@ -24,19 +24,24 @@ pub mod debugger;
 pub mod descriptor;
 pub mod faults;
 pub mod feature;
+pub mod fpu;
 pub mod idt;
 pub mod interrupts;
 pub mod ioport;
+pub mod itrace;
 pub mod mmu;
 pub mod mp;
 pub mod ops;
 pub mod page_tables;
+pub mod perfmon;
 pub mod registers;
 pub mod smp;
 pub mod syscalls;
 pub mod timer;
 pub mod tsc;
 pub mod uspace_entry;
+#[cfg(feature = "hypervisor")]
+pub mod vmx;
 
 // Sub-modules
 // pub mod hypervisor;  // TODO: Implement hypervisor module
@@ -60,6 +65,10 @@ pub use timer::*;
 pub use uspace_entry::*;
 
 // Types from iframe
+//
+// Laid out as interrupt_common (exceptions.S) leaves the stack: the
+// registers it pushes, the vector and error code pushed by the stub, then
+// the frame pushed by the CPU.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct X86Iframe {
@@ -67,13 +76,10 @@ pub struct X86Iframe {
     pub rdi: u64,
     pub rsi: u64,
     pub rbp: u64,
+    pub rbx: u64,
     pub rdx: u64,
     pub rcx: u64,
-    pub rbx: u64,
     pub rax: u64,
-    pub rsp: u64,
-
-    // Segment registers
     pub r8: u64,
     pub r9: u64,
     pub r10: u64,
@@ -83,11 +89,16 @@ pub struct X86Iframe {
     pub r14: u64,
     pub r15: u64,
 
-    // Special registers
-    pub rflags: u64,
+    // Pushed by the entry stub
+    pub vector: u64,
+    pub err_code: u64,
+
+    // Pushed by the CPU
     pub rip: u64,
-    pub user_ss: u64,
     pub user_cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub user_ss: u64,
 }
 
 // Thread context for AMD64
@@ -111,8 +122,11 @@ pub fn arch_early_init() {
         // Initialize MMU early
         ffi::sys_x86_mmu_early_init();
 
+        // Descriptor tables, the boot CPU's TSS and the SYSCALL MSRs
+        mp::x86_init_percpu(0);
+
         // Initialize extended registers (SSE/AVX)
-        ffi::sys_x86_extended_register_init();
+        fpu::x86_fpu_init();
 
         // Initialize CPU features
         ffi::sys_x86_feature_init();
@@ -122,9 +136,6 @@ pub fn arch_early_init() {
 // Main initialization
 pub fn arch_init() {
     unsafe {
-        // Initialize per-CPU data
-        ffi::sys_x86_init_percpu(0);
-
         // Initialize main MMU
         ffi::sys_x86_mmu_init();
 
@@ -9,6 +9,12 @@
 //! This module provides support for multiple CPU cores on x86-64.
 
 
+use crate::kernel::arch::amd64::descriptor::{self, TaskStateSegment, NUM_ASSIGNED_IST_ENTRIES};
+use crate::kernel::arch::amd64::ioport::IO_BITMAP_BYTES;
+use crate::kernel::arch::amd64::registers::{msr, write_msr};
+use crate::kernel::arch::amd64::syscalls;
+use crate::rustux::tls::{ZX_TLS_STACK_GUARD_OFFSET, ZX_TLS_UNSAFE_SP_OFFSET};
+use core::mem::offset_of;
 use core::sync::atomic::{AtomicU32, Ordering};
 
 /// Current CPU ID
@@ -48,39 +54,83 @@ pub fn x86_apic_id_to_cpu_num(apic_id: u32) -> i32 {
     }
 }
 
-/// Per-CPU current thread offset
-pub const PERCPU_CURRENT_THREAD_OFFSET: u32 = 0;
+/// Size of each interrupt stack
+pub const INTERRUPT_STACK_SIZE: usize = 4096;
 
-/// PerCPU structure placeholder
+/// A stack for the interrupt stack table
+#[repr(C, align(16))]
+pub struct InterruptStack(pub [u8; INTERRUPT_STACK_SIZE]);
+
+/// Per-CPU data, reached through the kernel GS base
 ///
-/// This is a minimal stub for the per-CPU data structure.
+/// The offsets of the fields the entry code and the compiler use are fixed;
+/// see the `PERCPU_*_OFFSET` constants.
 #[repr(C)]
 pub struct PerCpu {
-    /// APIC ID for this CPU
-    pub apic_id: u32,
+    /// This structure's own address, so `gs:[0]` yields a pointer to it
+    pub direct: *mut PerCpu,
     /// Current thread pointer
     pub current_thread: usize,
-    /// Default TSS RSP0 (kernel stack pointer)
-    pub default_tss: TssState,
-    /// Stack guard value
+    /// Stack guard value read by stack-protector code
     pub stack_guard: u64,
+    /// Unsafe stack pointer, when safe-stack is enabled
+    pub kernel_unsafe_sp: u64,
+    /// User RSP saved by the syscall entry while it switches stacks
+    pub saved_user_sp: u64,
     /// GPF return target for exception handling
     pub gpf_return_target: usize,
+    /// CPU number
+    pub cpu_num: u32,
+    /// APIC ID for this CPU
+    pub apic_id: u32,
+    /// This CPU's TSS; RSP0 is the kernel stack of the running thread
+    pub default_tss: TaskStateSegment,
+    /// I/O permission bitmap of the TSS, which must follow it, and the
+    /// all-ones byte the CPU expects past the end
+    pub io_bitmap: [u8; IO_BITMAP_BYTES + 1],
+    /// Generation of the process bitmap copied into `io_bitmap`, or 0 if
+    /// every port is denied
+    pub io_bitmap_generation: u64,
+    /// Stacks for the interrupt stack table slots, in slot order
+    pub interrupt_stacks: [InterruptStack; NUM_ASSIGNED_IST_ENTRIES],
 }
 
-/// TSS state placeholder
-#[repr(C)]
-pub struct TssState {
-    pub rsp0: u64,
+impl PerCpu {
+    /// Top of the stack for interrupt stack table slot `ist`
+    pub fn interrupt_stack_top(&self, ist: u8) -> u64 {
+        let stack = &self.interrupt_stacks[ist as usize - 1];
+        stack.0.as_ptr() as u64 + INTERRUPT_STACK_SIZE as u64
+    }
 }
 
+/// Offsets into [`PerCpu`] used from assembly
+pub const PERCPU_DIRECT_OFFSET: usize = offset_of!(PerCpu, direct);
+pub const PERCPU_CURRENT_THREAD_OFFSET: usize = offset_of!(PerCpu, current_thread);
+pub const PERCPU_SAVED_USER_SP_OFFSET: usize = offset_of!(PerCpu, saved_user_sp);
+pub const PERCPU_GPF_RETURN_OFFSET: usize = offset_of!(PerCpu, gpf_return_target);
+pub const PERCPU_CPU_NUM_OFFSET: usize = offset_of!(PerCpu, cpu_num);
+pub const PERCPU_DEFAULT_TSS_OFFSET: usize = offset_of!(PerCpu, default_tss);
+
+/// Offset of the kernel stack pointer: RSP0 of the TSS
+pub const PERCPU_KERNEL_SP_OFFSET: usize = PERCPU_DEFAULT_TSS_OFFSET + 4;
+
+// The compiler reads these through %gs at fixed offsets
+const _: () = assert!(PERCPU_DIRECT_OFFSET == 0);
+const _: () = assert!(offset_of!(PerCpu, stack_guard) == ZX_TLS_STACK_GUARD_OFFSET);
+const _: () = assert!(offset_of!(PerCpu, kernel_unsafe_sp) == ZX_TLS_UNSAFE_SP_OFFSET);
+
+// The TSS limit covers the I/O bitmap, so it has to sit right after the TSS
+const _: () = assert!(
+    offset_of!(PerCpu, io_bitmap) == PERCPU_DEFAULT_TSS_OFFSET + core::mem::size_of::<TaskStateSegment>()
+);
+
 /// Get the per-CPU structure for the current CPU
 ///
 /// # Safety
 ///
 /// This function assumes the per-CPU base is properly set up in GS.
 pub unsafe fn x86_get_percpu() -> *mut PerCpu {
-    // Read GS base to get per-CPU pointer
+    // The first field of the per-CPU structure points at itself
     let mut gs_base: u64;
     core::arch::asm!(
         "mov {}, gs:[0]",
@@ -99,12 +149,34 @@ pub fn cpu_num_to_mask(cpu_num: u32) -> u64 {
 
 /// Initialize per-CPU data
 ///
+/// Points GS at the CPU's per-CPU structure, loads the GDT, the CPU's TSS
+/// and the IDT, and sets up the SYSCALL MSRs. The boot CPU builds the
+/// shared GDT and IDT first. Also run on resume.
+///
 /// # Safety
 ///
-/// Must be called with a valid CPU number.
+/// Must be called with a valid CPU number, on that CPU, with interrupts
+/// disabled.
 pub unsafe fn x86_init_percpu(cpu_num: u32) {
+    let percpu = &mut PERCPUS[cpu_num as usize];
+    percpu.direct = percpu as *mut PerCpu;
+    percpu.cpu_num = cpu_num;
+
+    // The kernel runs with its GS base live; KERNEL_GS_BASE holds the
+    // user's while in the kernel, and swapgs exchanges them
+    write_msr(msr::IA32_GS_BASE, percpu as *mut PerCpu as u64);
+    write_msr(msr::IA32_KERNEL_GS_BASE, 0);
     x86_set_cpuid(cpu_num);
-    // TODO: Initialize per-CPU fields like current_thread, stack_guard, etc.
+
+    if cpu_num == 0 {
+        descriptor::gdt_setup();
+        descriptor::idt_setup();
+    }
+    descriptor::x86_load_gdt();
+    descriptor::x86_initialize_percpu_tss(percpu, cpu_num);
+    descriptor::x86_load_idt();
+
+    syscalls::x86_syscall_init();
 }
 
 /// Maximum number of CPUs supported
@@ -34,6 +34,7 @@ use core::sync::atomic::{spin::Mutex, Ordering};
 use super::constants::*;
 use super::super::include::arch::x86::page_tables::page_tables::*;
 use crate::kernel::pmm;
+use crate::vm::page_table::{count_large_mappings, MappingType, PageUsage};
 use crate::rustux::types::status;
 
 // Re-export from the include module
@@ -181,6 +182,45 @@ pub fn page_aligned(level: PageTableLevel, addr: usize) -> bool {
     addr & mask == 0
 }
 
+/// Shift of the region one entry at `level` maps
+pub fn level_shift(level: PageTableLevel) -> usize {
+    match level {
+        PageTableLevel::PT_L => PT_SHIFT,
+        PageTableLevel::PD_L => PD_SHIFT,
+        PageTableLevel::PDP_L => PDP_SHIFT,
+        PageTableLevel::PML4_L => PML4_SHIFT,
+    }
+}
+
+/// Level of the tables `level`'s entries point to
+fn lower_level(level: PageTableLevel) -> Option<PageTableLevel> {
+    match level {
+        PageTableLevel::PML4_L => Some(PageTableLevel::PDP_L),
+        PageTableLevel::PDP_L => Some(PageTableLevel::PD_L),
+        PageTableLevel::PD_L => Some(PageTableLevel::PT_L),
+        PageTableLevel::PT_L => None,
+    }
+}
+
+/// Whether a present `entry` at `level` maps memory rather than a table
+pub fn is_leaf(level: PageTableLevel, entry: u64) -> bool {
+    level == PageTableLevel::PT_L || entry & mmu_flags::X86_MMU_PG_PS != 0
+}
+
+/// Bytes from `vaddr` to the end of the region its entry at `level` maps
+fn bytes_to_entry_end(level: PageTableLevel, vaddr: usize) -> usize {
+    let size = 1usize << level_shift(level);
+    size - (vaddr & (size - 1))
+}
+
+/// Kind of large mapping a leaf at `level` is
+fn large_mapping_type(level: PageTableLevel) -> MappingType {
+    match level {
+        PageTableLevel::PDP_L => MappingType::Page1G,
+        _ => MappingType::Page2M,
+    }
+}
+
 /// Convert virtual address to index at given level
 pub fn vaddr_to_index(level: PageTableLevel, vaddr: usize) -> usize {
     match level {
@@ -344,43 +384,31 @@ impl X86PageTableBase {
             let entry_val = unsafe { read_volatile(entry) };
 
             // Check if we need to split a large page
-            if entry_val & X86_MMU_PG_P != 0 {
-                let is_large = entry_val & X86_MMU_PG_PS != 0;
-                let is_terminal = (entry_val & (X86_MMU_PG_RW | X86_MMU_PG_U)) != 0 || is_large;
-
-                if is_terminal && !is_large {
+            if entry_val & X86_MMU_PG_P != 0 && is_leaf(level, entry_val) {
+                if level == PageTableLevel::PT_L {
                     // Page already mapped at this level
                     return Err(status::ERR_BAD_STATE);
                 }
 
-                if is_large {
-                    // Split the large page
-                    self.split_large_page(level, cursor.vaddr, entry, cm)?;
-                    continue;
-                }
+                // Split the large page
+                self.split_large_page(level, cursor.vaddr, entry, cm)?;
+                continue;
             }
 
             // Determine if we should use a large page
-            let use_large = self.supports_page_size(level) &&
-                           cursor.size >= (1 << match level {
-                               PageTableLevel::PD_L => PD_SHIFT,
-                               PageTableLevel::PDP_L => PDP_SHIFT,
-                               _ => PT_SHIFT,
-                           }) &&
+            let page_size = 1usize << level_shift(level);
+            let use_large = level != PageTableLevel::PT_L &&
+                           entry_val & X86_MMU_PG_P == 0 &&
+                           self.supports_page_size(level) &&
+                           cursor.size >= page_size &&
                            page_aligned(level, cursor.vaddr) &&
                            page_aligned(level, cursor.paddr);
 
-            if level != PageTableLevel::PT_L && use_large {
+            if use_large {
                 // Create a large page mapping
-                let flags = self.terminal_flags(level, mmu_flags);
+                let flags = self.terminal_flags(level, mmu_flags) | X86_MMU_PG_PS;
                 self.update_entry(cm, level, cursor.vaddr, entry, cursor.paddr, flags, false);
-
-                let page_size = 1 << match level {
-                    PageTableLevel::PD_L => PD_SHIFT,
-                    PageTableLevel::PDP_L => PDP_SHIFT,
-                    _ => PT_SHIFT,
-                };
-
+                count_large_mappings(large_mapping_type(level), 1);
                 cursor.advance(page_size);
             } else if level == PageTableLevel::PT_L {
                 // Create a 4KB page mapping
@@ -389,6 +417,7 @@ impl X86PageTableBase {
                 cursor.advance(PAGE_SIZE);
             } else {
                 // Allocate intermediate page table
+                let mut entry_val = entry_val;
                 if entry_val & X86_MMU_PG_P == 0 {
                     // Allocate new page table
                     let (page, phys_addr) = pmm::alloc_page(0)
@@ -404,21 +433,16 @@ impl X86PageTableBase {
 
                     // Set the entry
                     let int_flags = self.intermediate_flags();
-                    let new_entry = (phys_addr as u64) | int_flags;
+                    entry_val = (phys_addr as u64) | int_flags;
                     unsafe {
-                        write_volatile(entry, new_entry);
+                        write_volatile(entry, entry_val);
                     }
 
                     *self.pages.lock() += 1;
                 }
 
-                // Follow to next level
-                let next_level = match level {
-                    PageTableLevel::PML4_L => PageTableLevel::PDP_L,
-                    PageTableLevel::PDP_L => PageTableLevel::PD_L,
-                    PageTableLevel::PD_L => PageTableLevel::PT_L,
-                    _ => return Err(status::ERR_BAD_STATE),
-                };
+                // Follow to next level, covering no more than this entry maps
+                let next_level = lower_level(level).ok_or(status::ERR_BAD_STATE)?;
 
                 let paddr = paddr_from_pte(level, entry_val);
                 let vaddr = x86_phys_to_virt(paddr);
@@ -426,7 +450,10 @@ impl X86PageTableBase {
                     core::slice::from_raw_parts_mut(vaddr as *mut PtEntry, ENTRIES_PER_PAGE_TABLE)
                 };
 
-                return self.add_mapping(next_table, mmu_flags, next_level, cursor, cm);
+                let chunk = cursor.size.min(bytes_to_entry_end(level, cursor.vaddr));
+                let mut sub = MappingCursor::new(cursor.vaddr, cursor.paddr, chunk);
+                self.add_mapping(next_table, mmu_flags, next_level, &mut sub, cm)?;
+                cursor.advance(chunk);
             }
         }
 
@@ -434,6 +461,9 @@ impl X86PageTableBase {
     }
 
     /// Remove a mapping from the page table
+    ///
+    /// A large page only partly inside the range is split first, so the
+    /// rest of it stays mapped.
     fn remove_mapping(
         &mut self,
         table: &mut [PtEntry],
@@ -448,38 +478,30 @@ impl X86PageTableBase {
             let entry = &mut table[index] as *mut PtEntry;
 
             let entry_val = unsafe { read_volatile(entry) };
+            let chunk = cursor.size.min(bytes_to_entry_end(level, cursor.vaddr));
 
             if entry_val & X86_MMU_PG_P == 0 {
-                // Page not mapped, skip
-                cursor.advance(PAGE_SIZE);
+                // Nothing mapped under this entry, skip it
+                cursor.advance(chunk);
                 continue;
             }
 
-            let is_large = entry_val & X86_MMU_PG_PS != 0;
-            let is_terminal = (entry_val & (X86_MMU_PG_RW | X86_MMU_PG_U)) != 0 || is_large;
-
-            if is_terminal {
-                // Remove the mapping
-                let page_size = if is_large {
-                    match level {
-                        PageTableLevel::PD_L => 1 << PD_SHIFT,
-                        PageTableLevel::PDP_L => 1 << PDP_SHIFT,
-                        _ => PAGE_SIZE,
+            if is_leaf(level, entry_val) {
+                if level != PageTableLevel::PT_L {
+                    if chunk != 1usize << level_shift(level) {
+                        // Only part of the large page goes
+                        self.split_large_page(level, cursor.vaddr, entry, cm)?;
+                        continue;
                     }
-                } else {
-                    PAGE_SIZE
-                };
+                    count_large_mappings(large_mapping_type(level), -1);
+                }
 
+                // Remove the mapping
                 self.unmap_entry(cm, level, cursor.vaddr, entry, true);
-                cursor.advance(page_size);
+                cursor.advance(chunk);
             } else {
                 // Recurse to next level
-                let next_level = match level {
-                    PageTableLevel::PML4_L => PageTableLevel::PDP_L,
-                    PageTableLevel::PDP_L => PageTableLevel::PD_L,
-                    PageTableLevel::PD_L => PageTableLevel::PT_L,
-                    _ => return Err(status::ERR_BAD_STATE),
-                };
+                let next_level = lower_level(level).ok_or(status::ERR_BAD_STATE)?;
 
                 let paddr = paddr_from_pte(level, entry_val);
                 let vaddr = x86_phys_to_virt(paddr);
@@ -487,34 +509,22 @@ impl X86PageTableBase {
                     core::slice::from_raw_parts_mut(vaddr as *mut PtEntry, ENTRIES_PER_PAGE_TABLE)
                 };
 
-                // Save the original cursor size to check if we emptied the table
-                let orig_size = cursor.size;
-                self.remove_mapping(next_table, next_level, cursor, cm)?;
-
-                // Check if the intermediate table is now empty
-                if cursor.size == orig_size {
-                    // Table was empty, check if all entries are zero
-                    let mut empty = true;
-                    for i in 0..ENTRIES_PER_PAGE_TABLE {
-                        if unsafe { read_volatile(&next_table[i]) } != 0 {
-                            empty = false;
-                            break;
-                        }
-                    }
-
-                    if empty {
-                        // Free the intermediate table
-                        self.unmap_entry(cm, level, cursor.vaddr, entry, false);
+                let mut sub = MappingCursor::new(cursor.vaddr, cursor.paddr, chunk);
+                self.remove_mapping(next_table, next_level, &mut sub, cm)?;
 
-                        if let Some(page) = pmm::paddr_to_vm_page(paddr) {
-                            pmm::free_page(page);
-                        }
+                // Free the intermediate table if that emptied it
+                let empty = next_table.iter().all(|e| unsafe { read_volatile(e) } == 0);
+                if empty {
+                    self.unmap_entry(cm, level, cursor.vaddr, entry, false);
 
-                        *self.pages.lock() -= 1;
+                    if let Some(page) = pmm::paddr_to_vm_page(paddr) {
+                        pmm::free_page(page);
                     }
 
-                    cursor.advance(PAGE_SIZE);
+                    *self.pages.lock() -= 1;
                 }
+
+                cursor.advance(chunk);
             }
         }
 
@@ -522,6 +532,9 @@ impl X86PageTableBase {
     }
 
     /// Update mappings in the page table
+    ///
+    /// Like [`remove_mapping`](Self::remove_mapping), splits a large page
+    /// the range only partly covers.
     fn update_mapping(
         &mut self,
         table: &mut [PtEntry],
@@ -537,29 +550,30 @@ impl X86PageTableBase {
             let entry = &mut table[index] as *mut PtEntry;
 
             let entry_val = unsafe { read_volatile(entry) };
+            let chunk = cursor.size.min(bytes_to_entry_end(level, cursor.vaddr));
 
             if entry_val & X86_MMU_PG_P == 0 {
                 // Skip unmapped pages (we may encounter these due to demand paging)
-                cursor.advance(PAGE_SIZE);
+                cursor.advance(chunk);
                 continue;
             }
 
-            let is_large = entry_val & X86_MMU_PG_PS != 0;
-            let is_terminal = (entry_val & (X86_MMU_PG_RW | X86_MMU_PG_U)) != 0 || is_large;
+            if is_leaf(level, entry_val) {
+                let mut term_flags = self.terminal_flags(level, mmu_flags);
+                if level != PageTableLevel::PT_L {
+                    if chunk != 1usize << level_shift(level) {
+                        self.split_large_page(level, cursor.vaddr, entry, cm)?;
+                        continue;
+                    }
+                    term_flags |= X86_MMU_PG_PS;
+                }
 
-            if is_terminal {
                 let paddr = paddr_from_pte(level, entry_val);
-                let term_flags = self.terminal_flags(level, mmu_flags);
                 self.update_entry(cm, level, cursor.vaddr, entry, paddr, term_flags, true);
-                cursor.advance(PAGE_SIZE);
+                cursor.advance(chunk);
             } else {
                 // Recurse to next level
-                let next_level = match level {
-                    PageTableLevel::PML4_L => PageTableLevel::PDP_L,
-                    PageTableLevel::PDP_L => PageTableLevel::PD_L,
-                    PageTableLevel::PD_L => PageTableLevel::PT_L,
-                    _ => return Err(status::ERR_BAD_STATE),
-                };
+                let next_level = lower_level(level).ok_or(status::ERR_BAD_STATE)?;
 
                 let paddr = paddr_from_pte(level, entry_val);
                 let vaddr = x86_phys_to_virt(paddr);
@@ -567,7 +581,9 @@ impl X86PageTableBase {
                     core::slice::from_raw_parts_mut(vaddr as *mut PtEntry, ENTRIES_PER_PAGE_TABLE)
                 };
 
-                self.update_mapping(next_table, mmu_flags, next_level, cursor, cm)?;
+                let mut sub = MappingCursor::new(cursor.vaddr, cursor.paddr, chunk);
+                self.update_mapping(next_table, mmu_flags, next_level, &mut sub, cm)?;
+                cursor.advance(chunk);
             }
         }
 
@@ -590,10 +606,7 @@ impl X86PageTableBase {
             return Err(status::ERR_NOT_FOUND);
         }
 
-        let is_large = entry & X86_MMU_PG_PS != 0;
-        let is_terminal = (entry & (X86_MMU_PG_RW | X86_MMU_PG_U)) != 0 || is_large;
-
-        if is_terminal {
+        if is_leaf(level, entry) {
             Ok((level, entry))
         } else if level != PageTableLevel::PT_L {
             let next_level = match level {
@@ -615,6 +628,53 @@ impl X86PageTableBase {
         }
     }
 
+    /// Read the accessed and dirty bits of the leaf mapping `vaddr`,
+    /// clearing them if `clear`
+    ///
+    /// The CPU sets both bits itself on the next access, so clearing only
+    /// needs the TLB entry dropped. The clear is atomic so that a bit the
+    /// CPU sets meanwhile is not lost.
+    fn harvest_mapping(
+        &mut self,
+        table: &mut [PtEntry],
+        vaddr: usize,
+        level: PageTableLevel,
+        clear: bool,
+        cm: &mut ConsistencyManager,
+    ) -> Result<(usize, PageUsage), Errno> {
+        use mmu_flags::*;
+
+        let index = vaddr_to_index(level, vaddr);
+        let entry = &mut table[index] as *mut PtEntry;
+        let entry_val = unsafe { read_volatile(entry) };
+
+        if entry_val & X86_MMU_PG_P == 0 {
+            return Err(status::ERR_NOT_FOUND);
+        }
+
+        if !is_leaf(level, entry_val) {
+            let next_level = lower_level(level).ok_or(status::ERR_BAD_STATE)?;
+            let virt = x86_phys_to_virt(paddr_from_pte(level, entry_val));
+            let next_table = unsafe {
+                core::slice::from_raw_parts_mut(virt as *mut PtEntry, ENTRIES_PER_PAGE_TABLE)
+            };
+            return self.harvest_mapping(next_table, vaddr, next_level, clear, cm);
+        }
+
+        let usage = PageUsage {
+            accessed: entry_val & X86_MMU_PG_A != 0,
+            dirty: entry_val & X86_MMU_PG_D != 0,
+        };
+        if clear && (usage.accessed || usage.dirty) {
+            let atomic = unsafe { &*(entry as *const core::sync::atomic::AtomicU64) };
+            atomic.fetch_and(!(X86_MMU_PG_A | X86_MMU_PG_D), Ordering::AcqRel);
+            cm.pending.enqueue(vaddr, level, entry_val & X86_MMU_PG_G != 0, true);
+        }
+
+        let offset = vaddr & ((1usize << level_shift(level)) - 1) & !(PAGE_SIZE - 1);
+        Ok((paddr_from_pte(level, entry_val) + offset, usage))
+    }
+
     /// Split a large page into smaller pages
     fn split_large_page(
         &mut self,
@@ -664,15 +724,19 @@ impl X86PageTableBase {
             next_table[i] = (entry_paddr as u64) | new_flags | X86_MMU_PG_P;
         }
 
-        // Update the original entry to point to the new table
+        // Update the original entry to point to the new table, and drop
+        // any TLB entry for the large page
         let int_flags = self.intermediate_flags();
-        let new_entry = (phys_addr as u64) | int_flags;
-        unsafe {
-            write_volatile(pte, new_entry);
-        }
+        self.update_entry(cm, level, vaddr & !((1 << level_shift(level)) - 1), pte,
+                          phys_addr, int_flags, true);
 
         *self.pages.lock() += 1;
 
+        count_large_mappings(large_mapping_type(level), -1);
+        if next_level != PageTableLevel::PT_L {
+            count_large_mappings(large_mapping_type(next_level), entry_count as i64);
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,128 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86 Performance Monitoring
+//!
+//! Intel architectural performance monitoring, as described by CPUID leaf
+//! 0xA: general-purpose counters programmed through `IA32_PERFEVTSELx`,
+//! read from `IA32_PMCx` and started and stopped together with
+//! `IA32_PERF_GLOBAL_CTRL`.
+//!
+//! Version 2 is required for the global controls and overflow status.
+//! Older CPUs and those without leaf 0xA, AMD among them, report no
+//! counters.
+//!
+//! Overflow interrupts are delivered through the local APIC's
+//! performance counter LVT entry on [`X86_INT_APIC_PMI`].
+//!
+//! [`X86_INT_APIC_PMI`]: crate::kernel::arch::amd64::faults::X86_INT_APIC_PMI
+
+use crate::kernel::arch::amd64::apic;
+use crate::kernel::arch::amd64::faults::X86_INT_APIC_PMI;
+use crate::kernel::arch::amd64::registers::*;
+use crate::kernel::lib::perfmon::*;
+use core::arch::x86_64::__cpuid_count;
+
+/// Architectural performance monitoring leaf
+const CPUID_PERFMON: u32 = 0xA;
+
+/// MSRs
+const IA32_PMC0: u32 = 0xC1;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+const IA32_PERF_GLOBAL_STATUS: u32 = 0x38E;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38F;
+const IA32_PERF_GLOBAL_OVF_CTRL: u32 = 0x390;
+
+/// `IA32_PERFEVTSELx` bits
+const PERFEVTSEL_USR: u64 = 1 << 16;
+const PERFEVTSEL_OS: u64 = 1 << 17;
+const PERFEVTSEL_INT: u64 = 1 << 20;
+const PERFEVTSEL_EN: u64 = 1 << 22;
+
+/// Architectural events, `umask << 8 | event`, with the CPUID.0xA:EBX bit
+/// that is set when each is unavailable
+const EVENT_CYCLES: (u64, u32) = (0x003C, 0);
+const EVENT_INSTRUCTIONS: (u64, u32) = (0x00C0, 1);
+const EVENT_LLC_MISSES: (u64, u32) = (0x412E, 4);
+const EVENT_BRANCH_MISSES: (u64, u32) = (0x00C5, 6);
+
+/// CPUID leaf 0xA: version, counters, counter width and the EBX
+/// unavailable-event mask
+fn leaf() -> (u32, u32, u32, u32) {
+    let max = unsafe { __cpuid_count(0, 0) }.eax;
+    if max < CPUID_PERFMON {
+        return (0, 0, 0, 0);
+    }
+    let r = unsafe { __cpuid_count(CPUID_PERFMON, 0) };
+    let ebx_len = (r.eax >> 24) & 0xff;
+    let unavailable = r.ebx | !((1u64 << ebx_len) as u32).wrapping_sub(1);
+    (r.eax & 0xff, (r.eax >> 8) & 0xff, (r.eax >> 16) & 0xff, unavailable)
+}
+
+/// Whether the CPU has version 2 or later
+fn supported() -> bool {
+    leaf().0 >= 2
+}
+
+pub fn x86_perfmon_counter_count() -> u32 {
+    if supported() { leaf().1 } else { 0 }
+}
+
+pub fn x86_perfmon_counter_bits() -> u32 {
+    if supported() { leaf().2 } else { 0 }
+}
+
+pub fn x86_perfmon_event(event: u32) -> Option<u64> {
+    let (hw, bit) = match event {
+        PERFMON_EVENT_CYCLES => EVENT_CYCLES,
+        PERFMON_EVENT_INSTRUCTIONS => EVENT_INSTRUCTIONS,
+        PERFMON_EVENT_CACHE_MISSES => EVENT_LLC_MISSES,
+        PERFMON_EVENT_BRANCH_MISSES => EVENT_BRANCH_MISSES,
+        _ => return None,
+    };
+    (supported() && leaf().3 & (1 << bit) == 0).then_some(hw)
+}
+
+pub unsafe fn x86_perfmon_init() {
+    if !supported() {
+        return;
+    }
+    apic::apic_pmi_unmask(X86_INT_APIC_PMI as u8);
+}
+
+pub unsafe fn x86_perfmon_program(index: u32, event: u64, value: u64, flags: u32) {
+    let mut evtsel = event & 0xffff;
+    if flags & PERFMON_FLAG_USER != 0 {
+        evtsel |= PERFEVTSEL_USR;
+    }
+    if flags & PERFMON_FLAG_KERNEL != 0 {
+        evtsel |= PERFEVTSEL_OS;
+    }
+    if flags & PERFMON_FLAG_INTERRUPT != 0 {
+        evtsel |= PERFEVTSEL_INT;
+    }
+
+    write_msr(IA32_PERFEVTSEL0 + index, 0);
+    // Writes through IA32_PMCx are sign-extended from bit 31, which
+    // turns the low half of a reload value into the whole of it
+    write_msr(IA32_PMC0 + index, value);
+    // Counts only once its IA32_PERF_GLOBAL_CTRL bit is set too
+    write_msr(IA32_PERFEVTSEL0 + index, evtsel | PERFEVTSEL_EN);
+}
+
+pub unsafe fn x86_perfmon_enable(mask: u32) {
+    write_msr(IA32_PERF_GLOBAL_CTRL, mask as u64);
+}
+
+pub fn x86_perfmon_read(index: u32) -> u64 {
+    unsafe { read_msr(IA32_PMC0 + index) }
+}
+
+pub unsafe fn x86_perfmon_ack_overflow() -> u32 {
+    let status = read_msr(IA32_PERF_GLOBAL_STATUS) & 0xffff_ffff;
+    write_msr(IA32_PERF_GLOBAL_OVF_CTRL, status);
+    status as u32
+}
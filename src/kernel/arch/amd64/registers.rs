@@ -24,6 +24,18 @@ pub mod msr {
     /// IA32_EFER - Extended Feature Enable Register
     pub const IA32_EFER: u32 = 0xC000_0080;
 
+    /// IA32_STAR - SYSCALL/SYSRET segment selectors
+    pub const IA32_STAR: u32 = 0xC000_0081;
+
+    /// IA32_LSTAR - SYSCALL target in 64-bit mode
+    pub const IA32_LSTAR: u32 = 0xC000_0082;
+
+    /// IA32_CSTAR - SYSCALL target in compatibility mode
+    pub const IA32_CSTAR: u32 = 0xC000_0083;
+
+    /// IA32_FMASK - RFLAGS bits cleared by SYSCALL
+    pub const IA32_FMASK: u32 = 0xC000_0084;
+
     /// IA32_APIC_BASE - Local APIC Base
     pub const IA32_APIC_BASE: u32 = 0x0000_001B;
 }
@@ -37,6 +49,11 @@ pub mod cr {
     pub const CR0_AM: u64 = 1 << 18;  // Alignment Mask
     pub const CR0_WP: u64 = 1 << 16;  // Write Protect
     pub const CR0_NE: u64 = 1 << 5;   // Numeric Error
+    pub const CR0_ET: u64 = 1 << 4;   // Extension Type
+    pub const CR0_TS: u64 = 1 << 3;   // Task Switched
+    pub const CR0_EM: u64 = 1 << 2;   // x87 Emulation
+    pub const CR0_MP: u64 = 1 << 1;   // Monitor Coprocessor
+    pub const CR0_PE: u64 = 1 << 0;   // Protection Enable
 
     /// CR4 - Control Register 4
     pub const CR4_PSE: u64 = 1 << 4;   // Page Size Extension
@@ -276,13 +293,16 @@ pub unsafe fn x86_read_debug_status(debug_state: &mut X86DebugState) {
     debug_state.dr6 = x86_read_dr6();
 }
 
+/// DR6 with no debug condition recorded
+pub const X86_DR6_INIT: u64 = 0xffff_0ff0;
+
 /// Read DR6 register
 ///
 /// # Safety
 ///
 /// This function uses inline assembly to read DR6.
 #[inline]
-unsafe fn x86_read_dr6() -> u64 {
+pub unsafe fn x86_read_dr6() -> u64 {
     let dr6: u64;
     core::arch::asm!(
         "mov {}, dr6",
@@ -292,6 +312,23 @@ unsafe fn x86_read_dr6() -> u64 {
     dr6
 }
 
+/// Write DR6 register
+///
+/// The CPU never clears DR6, so the #DB handler resets it to
+/// [`X86_DR6_INIT`].
+///
+/// # Safety
+///
+/// This function uses inline assembly to write DR6.
+#[inline]
+pub unsafe fn x86_write_dr6(dr6: u64) {
+    core::arch::asm!(
+        "mov dr6, {}",
+        in(reg) dr6,
+        options(nomem, nostack)
+    );
+}
+
 /// ============================================================================
 /// Control Register Functions
 /// ============================================================================
@@ -45,6 +45,23 @@ pub fn x86_init_smp(apic_ids: &[u32], num_cpus: u32) {
     unsafe { lk_init_secondary_cpus(num_cpus - 1) };
 }
 
+/// Initialize SMP using the CPUs enumerated by the ACPI MADT
+///
+/// Falls back to uniprocessor operation if no MADT was found.
+pub fn x86_init_smp_from_acpi() {
+    let mut apic_ids = [0u32; mp::MAX_CPUS];
+    let count = crate::kernel::dev::acpi::cpu_apic_ids(&mut apic_ids)
+        .min(u8::MAX as usize);
+
+    if count <= 1 {
+        println!("SMP: no additional CPUs in MADT, running uniprocessor");
+        return;
+    }
+
+    println!("SMP: {} CPUs from MADT", count);
+    x86_init_smp(&apic_ids[..count], count as u32);
+}
+
 /// Bring up the AP (Application Processor) cores
 ///
 /// # Arguments
@@ -315,6 +332,7 @@ extern "C" {
     fn x86_secondary_cpu_long_mode_entry();
 
     /// Allocate AP structures for the given APIC IDs
+    #[link_name = "sys_x86_allocate_ap_structures"]
     fn x86_allocate_ap_structures(apic_ids: *const u32, num_cpus: u8) -> i32;
 
     /// Convert APIC ID to CPU number
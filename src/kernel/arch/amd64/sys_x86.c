@@ -99,8 +99,8 @@ static inline void sfence(void) {
 #define X86_MSR_IA32_MTRR_CAP     0x0FE
 #define X86_MSR_IA32_MTRR_DEF     0x2FF
 
-/* Default PAT value: write-back caching for all entries */
-#define X86_PAT_DEFAULT_VALUE     0x0007010600070106ULL
+/* PAT value: WB, WT, UC-, UC, then WC at PA4 (see mmu.rs) */
+#define X86_PAT_DEFAULT_VALUE     0x0007040100070406ULL
 
 /* ============ Page Table Functions ============ */
 
@@ -8,7 +8,19 @@
 //!
 //! This module provides the system call entry point and dispatch for AMD64.
 //! It uses the `syscall` and `sysret` instructions for fast system calls.
-
+//!
+//! # GS Discipline
+//!
+//! In the kernel the GS base is the per-CPU structure; in user mode it is
+//! the user's, with the kernel's parked in IA32_KERNEL_GS_BASE. SYSCALL
+//! does not switch stacks or GS, so [`amd64_syscall`] swaps GS as its
+//! first instruction, before touching memory, and swaps back as the last
+//! thing before SYSRET. Interrupt entries swap only when they interrupted
+//! user mode (exceptions.S).
+
+use crate::kernel::arch::amd64::descriptor;
+use crate::kernel::arch::amd64::mp;
+use crate::kernel::arch::amd64::registers::{efer, msr, read_msr, rflags, write_msr};
 use crate::kernel::arch::amd64::X86Iframe;
 use crate::rustux::types::*;
 
@@ -92,25 +104,96 @@ pub mod syscall_ret {
     pub const EAGAIN: i64 = -11;  // Try again
 }
 
-/// Initialize the system call subsystem
+/// RFLAGS bits SYSCALL clears
 ///
-/// This sets up the syscall MSR (MSR_LSTAR) to point to our syscall entry point.
-pub fn syscall_init() {
-    unsafe {
-        use crate::kernel::arch::amd64::mmu;
+/// Interrupts stay off until the entry is on the kernel stack, and user
+/// TF, DF and AC must not leak into the kernel.
+pub const SYSCALL_FMASK: u64 = rflags::IF | rflags::TF | rflags::DF | rflags::AC | rflags::NT;
 
-        // MSR for syscall entry point (RIP in long mode)
-        const IA32_LSTAR_MSR: u32 = 0xC0000082;
-
-        // Set the syscall entry point
-        let syscall_entry = x86_syscall_entry as u64;
-        mmu::x86_write_msr(IA32_LSTAR_MSR, syscall_entry);
-
-        // TODO: Set up IA32_STAR_MSR for compatibility mode syscalls
-        // TODO: Set up IA32_FMASK_MSR for RFLAG masking
+/// Initialize the system call subsystem on the current CPU
+///
+/// Enables SYSCALL/SYSRET, sets their selectors from the GDT layout, and
+/// points IA32_LSTAR at [`amd64_syscall`]. The 32-bit entry is left at
+/// zero, as there is no compatibility mode user code segment.
+pub fn x86_syscall_init() {
+    unsafe {
+        write_msr(msr::IA32_EFER, read_msr(msr::IA32_EFER) | efer::SCE);
+        write_msr(msr::IA32_STAR, descriptor::x86_star_value());
+        write_msr(msr::IA32_LSTAR, amd64_syscall as usize as u64);
+        write_msr(msr::IA32_CSTAR, 0);
+        write_msr(msr::IA32_FMASK, SYSCALL_FMASK);
     }
 }
 
+/// SYSCALL entry point (IA32_LSTAR)
+///
+/// Switches to the thread's kernel stack (TSS RSP0), calls
+/// [`amd64_syscall_entry`](crate::kernel::syscalls::amd64_syscall_entry)
+/// with the syscall number as its seventh argument, and returns with the
+/// result in RAX. Argument registers are cleared before returning so no
+/// kernel values leak.
+///
+/// SYSRET raises #GP in ring 0 if RCX is not canonical, on the user's
+/// stack; a return to a non-canonical address goes through IRETQ instead,
+/// which faults in user mode.
+#[unsafe(naked)]
+#[no_mangle]
+pub unsafe extern "C" fn amd64_syscall() -> ! {
+    core::arch::naked_asm!(
+        "swapgs",
+        "mov gs:[{saved_user_sp}], rsp",
+        "mov rsp, gs:[{kernel_sp}]",
+        "push qword ptr gs:[{saved_user_sp}]",
+        "push r11",                  // user RFLAGS
+        "push rcx",                  // user RIP
+        "push rax",                  // syscall number, 7th argument
+        "mov rcx, r10",              // 4th argument
+        "sti",
+        "call {entry}",
+        "cli",
+        "add rsp, 8",
+        "pop rcx",
+        "pop r11",
+
+        // Clear the argument registers; RAX holds the result
+        "xor edi, edi",
+        "xor esi, esi",
+        "xor edx, edx",
+        "xor r8d, r8d",
+        "xor r9d, r9d",
+        "xor r10d, r10d",
+
+        // Sign-extending bit 47 leaves a canonical address unchanged
+        "mov rdi, rcx",
+        "shl rdi, 16",
+        "sar rdi, 16",
+        "cmp rdi, rcx",
+        "jne 2f",
+        "xor edi, edi",
+        "pop rsp",
+        "swapgs",
+        "sysretq",
+
+        "2:",
+        "xor edi, edi",
+        "pop rdx",                   // user RSP
+        "push {user_ss}",
+        "push rdx",
+        "push r11",
+        "push {user_cs}",
+        "push rcx",
+        "xor edx, edx",
+        "swapgs",
+        "iretq",
+
+        saved_user_sp = const mp::PERCPU_SAVED_USER_SP_OFFSET,
+        kernel_sp = const mp::PERCPU_KERNEL_SP_OFFSET,
+        user_ss = const descriptor::USER_DATA_SELECTOR as u64,
+        user_cs = const descriptor::USER_CODE_64_SELECTOR as u64,
+        entry = sym crate::kernel::syscalls::amd64_syscall_entry,
+    );
+}
+
 /// System call entry point (called from assembly)
 ///
 /// # Arguments
@@ -239,42 +322,6 @@ fn sys_debug_print(_str: u64, _len: u64) -> i64 {
     syscall_ret::OK
 }
 
-/// Save all general-purpose registers (for syscall context switch)
-///
-/// This is called from assembly to save the full user register state
-/// before entering the kernel.
-#[unsafe(naked)]
-#[no_mangle]
-pub unsafe extern "sysv64" fn x86syscall_save_all_gsbase() -> ! {
-    core::arch::naked_asm!(
-        "swapgs",                    // Swap to kernel GS
-        "mov gs:[8], rsp",          // Save user RSP to kernel stack
-        "push rax",                  // Save RAX (syscall number)
-        "push rcx",                  // Save RCX (return address)
-        "push r11",                  // Save R11 (RFLAGS)
-        "push rbp",                  // Save frame pointer
-        "jmp x86_syscall_entry",     // Jump to C entry
-    );
-}
-
-/// Restore all general-purpose registers and return to user space
-///
-/// This is called from assembly to restore the user register state
-/// and return from the system call.
-#[unsafe(naked)]
-#[no_mangle]
-pub unsafe extern "sysv64" fn x86syscall_restore_all_gsbase() -> ! {
-    core::arch::naked_asm!(
-        "pop rbp",                   // Restore frame pointer
-        "pop r11",                   // Restore RFLAGS
-        "pop rcx",                   // Restore return address to RCX
-        "pop rax",                   // Restore return value to RAX
-        "mov gs:[8], rsp",           // Restore user RSP
-        "swapgs",                    // Swap back to user GS
-        "sysretq",                   // Return to user space
-    );
-}
-
 /// Get the kernel GS base value
 ///
 /// Returns the base address of the kernel per-CPU data area
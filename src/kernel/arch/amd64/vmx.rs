@@ -0,0 +1,1909 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Intel VMX Guest Backend
+//!
+//! Runs [`crate::kernel::hypervisor`] guests with VT-x and extended page
+//! tables, on kernels built with the `hypervisor` feature.
+//!
+//! # Design
+//!
+//! - **VMX only while a guest runs**: [`VmxVcpu::enter`] disables
+//!   interrupts, turns VMX on, loads the vCPU's VMCS and runs the guest
+//!   until an exit the kernel can't finish by itself. It then clears the
+//!   VMCS and turns VMX off again. A vCPU is not tied to a CPU, and the
+//!   rest of the kernel never runs in VMX operation.
+//! - **64-bit guests**: a vCPU starts in long mode with paging on, at the
+//!   `entry` it was created with and with CR3 from its register state. The
+//!   VMM builds the guest's first page tables in guest memory.
+//! - **Host interrupts exit the guest**: they stay pending and are taken
+//!   once `enter` returns. The VMX-preemption timer, where the CPU has
+//!   one, also makes the guest exit at least every [`SLICE_TSC_TICKS`], so
+//!   a kick never waits for long.
+//! - **Exits handled here**: CPUID, MSRs, debug registers and interrupt
+//!   windows. Instructions the guest may not use, such as the VMX
+//!   instructions and XSETBV, raise #UD in the guest; unknown MSRs and
+//!   writes to the CR0 and CR4 bits VMX fixes raise #GP.
+//!
+//! Guest memory is an [`IoPageTable`] in EPT format. Unmapping waits until
+//! every vCPU that was in the guest has left it once, so no CPU still
+//! holds a stale translation when the VMO page is released.
+
+use crate::kernel::arch::amd64::descriptor::{self, GdtPointer, IdtPointer};
+use crate::kernel::arch::amd64::fpu::{self, X86FpuArea};
+use crate::kernel::arch::amd64::registers::cr::*;
+use crate::kernel::arch::amd64::registers::{
+    efer, msr, read_msr, rflags, write_msr, x86_get_cr0, x86_get_cr3, x86_get_cr4, x86_set_cr4,
+};
+use crate::kernel::arch::{arch_interrupt_restore, arch_interrupt_save};
+use crate::kernel::dev::iommu::page_table::{IoPageTable, PteFormat};
+use crate::kernel::dev::iommu::perm;
+use crate::kernel::hypervisor::{
+    vcpu_state, GuestExit, GuestPhysicalAddressSpace, MemAccess, VcpuState, MAX_VCPUS,
+};
+use crate::kernel::percpu;
+use crate::kernel::pmm;
+use crate::rustux::types::err::*;
+use crate::rustux::types::*;
+use alloc::sync::Arc;
+use core::arch::x86_64::{CpuidResult, __cpuid_count};
+use core::sync::atomic::{fence, AtomicU64, Ordering};
+
+use crate::log_error;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Longest x86 instruction
+const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// TSC ticks a guest runs before the preemption timer makes it exit,
+/// about a millisecond at 2 GHz
+pub const SLICE_TSC_TICKS: u64 = 1 << 21;
+
+/// ============================================================================
+/// Registers and VMCS Fields
+/// ============================================================================
+
+/// VMX capability MSRs
+mod vmx_msr {
+    pub const IA32_FEATURE_CONTROL: u32 = 0x3A;
+    pub const IA32_SYSENTER_CS: u32 = 0x174;
+    pub const IA32_SYSENTER_ESP: u32 = 0x175;
+    pub const IA32_SYSENTER_EIP: u32 = 0x176;
+    pub const IA32_VMX_BASIC: u32 = 0x480;
+    pub const IA32_VMX_MISC: u32 = 0x485;
+    pub const IA32_VMX_CR0_FIXED0: u32 = 0x486;
+    pub const IA32_VMX_CR0_FIXED1: u32 = 0x487;
+    pub const IA32_VMX_CR4_FIXED0: u32 = 0x488;
+    pub const IA32_VMX_CR4_FIXED1: u32 = 0x489;
+    pub const IA32_VMX_PROCBASED_CTLS2: u32 = 0x48B;
+    pub const IA32_VMX_EPT_VPID_CAP: u32 = 0x48C;
+    pub const IA32_VMX_TRUE_PINBASED_CTLS: u32 = 0x48D;
+    pub const IA32_VMX_TRUE_PROCBASED_CTLS: u32 = 0x48E;
+    pub const IA32_VMX_TRUE_EXIT_CTLS: u32 = 0x48F;
+    pub const IA32_VMX_TRUE_ENTRY_CTLS: u32 = 0x490;
+}
+
+/// IA32_FEATURE_CONTROL bits
+const FEATURE_CONTROL_LOCK: u64 = 1 << 0;
+const FEATURE_CONTROL_VMXON_OUTSIDE_SMX: u64 = 1 << 2;
+
+/// IA32_VMX_BASIC bits
+const BASIC_MEMORY_TYPE_SHIFT: u64 = 50;
+const BASIC_TRUE_CONTROLS: u64 = 1 << 55;
+
+/// IA32_VMX_EPT_VPID_CAP bits
+const EPT_CAP_4_LEVEL: u64 = 1 << 6;
+const EPT_CAP_WB: u64 = 1 << 14;
+const EPT_CAP_INVEPT: u64 = 1 << 20;
+const EPT_CAP_INVEPT_SINGLE: u64 = 1 << 25;
+
+/// Write-back memory type
+const MEMORY_TYPE_WB: u64 = 6;
+
+/// CPUID.1:ECX bits
+const CPUID_1_ECX_VMX: u32 = 1 << 5;
+
+/// VMCS field encodings
+mod vmcs {
+    // 16-bit guest state
+    pub const GUEST_ES_SELECTOR: u32 = 0x0800;
+
+    // 16-bit host state
+    pub const HOST_ES_SELECTOR: u32 = 0x0C00;
+    pub const HOST_CS_SELECTOR: u32 = 0x0C02;
+    pub const HOST_SS_SELECTOR: u32 = 0x0C04;
+    pub const HOST_DS_SELECTOR: u32 = 0x0C06;
+    pub const HOST_FS_SELECTOR: u32 = 0x0C08;
+    pub const HOST_GS_SELECTOR: u32 = 0x0C0A;
+    pub const HOST_TR_SELECTOR: u32 = 0x0C0C;
+
+    // 64-bit controls
+    pub const VIRTUAL_APIC_ADDRESS: u32 = 0x2012;
+    pub const EPT_POINTER: u32 = 0x201A;
+
+    // 64-bit exit information
+    pub const GUEST_PHYSICAL_ADDRESS: u32 = 0x2400;
+
+    // 64-bit guest state
+    pub const VMCS_LINK_POINTER: u32 = 0x2800;
+    pub const GUEST_IA32_DEBUGCTL: u32 = 0x2802;
+    pub const GUEST_IA32_EFER: u32 = 0x2806;
+
+    // 64-bit host state
+    pub const HOST_IA32_EFER: u32 = 0x2C02;
+
+    // 32-bit controls
+    pub const PIN_BASED_CONTROLS: u32 = 0x4000;
+    pub const PROCESSOR_CONTROLS: u32 = 0x4002;
+    pub const EXCEPTION_BITMAP: u32 = 0x4004;
+    pub const PAGE_FAULT_ERROR_MASK: u32 = 0x4006;
+    pub const PAGE_FAULT_ERROR_MATCH: u32 = 0x4008;
+    pub const CR3_TARGET_COUNT: u32 = 0x400A;
+    pub const EXIT_CONTROLS: u32 = 0x400C;
+    pub const EXIT_MSR_STORE_COUNT: u32 = 0x400E;
+    pub const EXIT_MSR_LOAD_COUNT: u32 = 0x4010;
+    pub const ENTRY_CONTROLS: u32 = 0x4012;
+    pub const ENTRY_MSR_LOAD_COUNT: u32 = 0x4014;
+    pub const ENTRY_INTERRUPTION_INFO: u32 = 0x4016;
+    pub const ENTRY_EXCEPTION_ERROR_CODE: u32 = 0x4018;
+    pub const ENTRY_INSTRUCTION_LENGTH: u32 = 0x401A;
+    pub const TPR_THRESHOLD: u32 = 0x401C;
+    pub const SECONDARY_CONTROLS: u32 = 0x401E;
+
+    // 32-bit exit information
+    pub const VM_INSTRUCTION_ERROR: u32 = 0x4400;
+    pub const EXIT_REASON: u32 = 0x4402;
+    pub const EXIT_INTERRUPTION_INFO: u32 = 0x4404;
+    pub const IDT_VECTORING_INFO: u32 = 0x4408;
+    pub const IDT_VECTORING_ERROR_CODE: u32 = 0x440A;
+    pub const EXIT_INSTRUCTION_LENGTH: u32 = 0x440C;
+
+    // 32-bit guest state
+    pub const GUEST_ES_LIMIT: u32 = 0x4800;
+    pub const GUEST_GDTR_LIMIT: u32 = 0x4810;
+    pub const GUEST_IDTR_LIMIT: u32 = 0x4812;
+    pub const GUEST_ES_ACCESS_RIGHTS: u32 = 0x4814;
+    pub const GUEST_CS_ACCESS_RIGHTS: u32 = 0x4816;
+    pub const GUEST_INTERRUPTIBILITY: u32 = 0x4824;
+    pub const GUEST_ACTIVITY_STATE: u32 = 0x4826;
+    pub const GUEST_IA32_SYSENTER_CS: u32 = 0x482A;
+    pub const PREEMPTION_TIMER_VALUE: u32 = 0x482E;
+
+    // 32-bit host state
+    pub const HOST_IA32_SYSENTER_CS: u32 = 0x4C00;
+
+    // Natural-width controls
+    pub const CR0_GUEST_HOST_MASK: u32 = 0x6000;
+    pub const CR4_GUEST_HOST_MASK: u32 = 0x6002;
+    pub const CR0_READ_SHADOW: u32 = 0x6004;
+    pub const CR4_READ_SHADOW: u32 = 0x6006;
+
+    // Natural-width exit information
+    pub const EXIT_QUALIFICATION: u32 = 0x6400;
+
+    // Natural-width guest state
+    pub const GUEST_CR0: u32 = 0x6800;
+    pub const GUEST_CR3: u32 = 0x6802;
+    pub const GUEST_CR4: u32 = 0x6804;
+    pub const GUEST_ES_BASE: u32 = 0x6806;
+    pub const GUEST_FS_BASE: u32 = 0x680E;
+    pub const GUEST_GS_BASE: u32 = 0x6810;
+    pub const GUEST_GDTR_BASE: u32 = 0x6816;
+    pub const GUEST_IDTR_BASE: u32 = 0x6818;
+    pub const GUEST_DR7: u32 = 0x681A;
+    pub const GUEST_RSP: u32 = 0x681C;
+    pub const GUEST_RIP: u32 = 0x681E;
+    pub const GUEST_RFLAGS: u32 = 0x6820;
+    pub const GUEST_PENDING_DEBUG_EXCEPTIONS: u32 = 0x6822;
+    pub const GUEST_IA32_SYSENTER_ESP: u32 = 0x6824;
+    pub const GUEST_IA32_SYSENTER_EIP: u32 = 0x6826;
+
+    // Natural-width host state
+    pub const HOST_CR0: u32 = 0x6C00;
+    pub const HOST_CR3: u32 = 0x6C02;
+    pub const HOST_CR4: u32 = 0x6C04;
+    pub const HOST_FS_BASE: u32 = 0x6C06;
+    pub const HOST_GS_BASE: u32 = 0x6C08;
+    pub const HOST_TR_BASE: u32 = 0x6C0A;
+    pub const HOST_GDTR_BASE: u32 = 0x6C0C;
+    pub const HOST_IDTR_BASE: u32 = 0x6C0E;
+    pub const HOST_IA32_SYSENTER_ESP: u32 = 0x6C10;
+    pub const HOST_IA32_SYSENTER_EIP: u32 = 0x6C12;
+    pub const HOST_RSP: u32 = 0x6C14;
+    pub const HOST_RIP: u32 = 0x6C16;
+}
+
+/// Pin-based controls
+const PIN_EXTERNAL_INTERRUPT_EXITING: u32 = 1 << 0;
+const PIN_NMI_EXITING: u32 = 1 << 3;
+const PIN_PREEMPTION_TIMER: u32 = 1 << 6;
+
+/// Primary processor-based controls
+const PROC_INTERRUPT_WINDOW_EXITING: u32 = 1 << 2;
+const PROC_HLT_EXITING: u32 = 1 << 7;
+const PROC_RDPMC_EXITING: u32 = 1 << 11;
+const PROC_TPR_SHADOW: u32 = 1 << 21;
+const PROC_MOV_DR_EXITING: u32 = 1 << 23;
+const PROC_UNCONDITIONAL_IO_EXITING: u32 = 1 << 24;
+const PROC_SECONDARY_CONTROLS: u32 = 1 << 31;
+
+/// Secondary processor-based controls
+const PROC2_EPT: u32 = 1 << 1;
+
+/// Exit controls
+const EXIT_HOST_64BIT: u32 = 1 << 9;
+const EXIT_SAVE_EFER: u32 = 1 << 20;
+const EXIT_LOAD_EFER: u32 = 1 << 21;
+
+/// Entry controls
+const ENTRY_IA32E_GUEST: u32 = 1 << 9;
+const ENTRY_LOAD_EFER: u32 = 1 << 15;
+
+/// Exit reasons
+mod exit_reason {
+    pub const EXCEPTION_OR_NMI: u32 = 0;
+    pub const EXTERNAL_INTERRUPT: u32 = 1;
+    pub const TRIPLE_FAULT: u32 = 2;
+    pub const INTERRUPT_WINDOW: u32 = 7;
+    pub const CPUID: u32 = 10;
+    pub const GETSEC: u32 = 11;
+    pub const HLT: u32 = 12;
+    pub const INVD: u32 = 13;
+    pub const RDPMC: u32 = 15;
+    pub const VMCALL: u32 = 18;
+    pub const VMXON: u32 = 27;
+    pub const CONTROL_REGISTER: u32 = 28;
+    pub const MOV_DR: u32 = 29;
+    pub const IO: u32 = 30;
+    pub const RDMSR: u32 = 31;
+    pub const WRMSR: u32 = 32;
+    pub const EPT_VIOLATION: u32 = 48;
+    pub const INVEPT: u32 = 50;
+    pub const PREEMPTION_TIMER: u32 = 52;
+    pub const INVVPID: u32 = 53;
+    pub const XSETBV: u32 = 55;
+
+    /// Set in the exit reason when VM entry itself failed
+    pub const ENTRY_FAILURE: u32 = 1 << 31;
+}
+
+/// Interruption information bits, for event injection and IDT vectoring
+const INTERRUPTION_VALID: u32 = 1 << 31;
+const INTERRUPTION_DELIVER_ERROR_CODE: u32 = 1 << 11;
+const INTERRUPTION_TYPE_SHIFT: u32 = 8;
+const INTERRUPTION_TYPE_MASK: u32 = 7;
+const INTERRUPTION_TYPE_NMI: u32 = 2;
+const INTERRUPTION_TYPE_HARDWARE_EXCEPTION: u32 = 3;
+
+/// Guest interruptibility bits
+const BLOCKING_BY_STI: u32 = 1 << 0;
+const BLOCKING_BY_MOV_SS: u32 = 1 << 1;
+
+/// Exception vectors raised in the guest
+const EXCEPTION_UD: u8 = 6;
+const EXCEPTION_GP: u8 = 13;
+
+/// Guest segment access rights
+const ACCESS_RIGHTS_CODE_64: u64 = 0xA09B;
+const ACCESS_RIGHTS_DATA: u64 = 0xC093;
+const ACCESS_RIGHTS_TSS_BUSY: u64 = 0x008B;
+const ACCESS_RIGHTS_UNUSABLE: u64 = 1 << 16;
+
+/// Guest segments as (selector, limit, access rights), in VMCS field order:
+/// ES, CS, SS, DS, FS, GS, LDTR, TR
+const GUEST_SEGMENTS: [(u64, u64, u64); 8] = [
+    (0x10, 0xFFFF_FFFF, ACCESS_RIGHTS_DATA),
+    (0x08, 0xFFFF_FFFF, ACCESS_RIGHTS_CODE_64),
+    (0x10, 0xFFFF_FFFF, ACCESS_RIGHTS_DATA),
+    (0x10, 0xFFFF_FFFF, ACCESS_RIGHTS_DATA),
+    (0x10, 0xFFFF_FFFF, ACCESS_RIGHTS_DATA),
+    (0x10, 0xFFFF_FFFF, ACCESS_RIGHTS_DATA),
+    (0, 0, ACCESS_RIGHTS_UNUSABLE),
+    (0, 0xFFFF, ACCESS_RIGHTS_TSS_BUSY),
+];
+
+/// Code segment long-mode bit in the access rights
+const ACCESS_RIGHTS_LONG_MODE: u64 = 1 << 13;
+
+/// DR7 after reset
+const DR7_INIT: u64 = 0x400;
+
+/// RFLAGS bits a guest may hold; bit 1 is always set
+const RFLAGS_FIXED: u64 = 1 << 1;
+const RFLAGS_VALID: u64 = rflags::CF
+    | RFLAGS_FIXED
+    | rflags::PF
+    | rflags::AF
+    | rflags::ZF
+    | rflags::SF
+    | rflags::TF
+    | rflags::IF
+    | rflags::DF
+    | rflags::OF
+    | rflags::IOPL
+    | rflags::NT
+    | rflags::RF
+    | rflags::AC
+    | rflags::VIF
+    | rflags::VIP
+    | rflags::ID;
+
+/// EFER bits a guest may set
+const EFER_GUEST_VALID: u64 = efer::SCE | efer::LME | efer::LMA | efer::NXE;
+
+/// MSRs the guest's instructions use directly, loaded around each run
+const SWAPPED_MSRS: [u32; 5] = [
+    msr::IA32_KERNEL_GS_BASE,
+    msr::IA32_STAR,
+    msr::IA32_LSTAR,
+    msr::IA32_CSTAR,
+    msr::IA32_FMASK,
+];
+
+/// Guest page table entry bits
+const PTE_PRESENT: u64 = 1 << 0;
+const PTE_LARGE: u64 = 1 << 7;
+const PTE_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// CPUID leaves the guest sees as empty
+const CPUID_PERFMON: u32 = 0xA;
+const CPUID_XSAVE: u32 = 0xD;
+const CPUID_HYPERVISOR_FIRST: u32 = 0x4000_0000;
+const CPUID_HYPERVISOR_LAST: u32 = 0x4000_00FF;
+
+/// CPUID.1 features hidden from the guest: MCE, MTRR and MCA in EDX; VMX,
+/// SMX, PDCM, x2APIC, TSC deadline, XSAVE, OSXSAVE and AVX in ECX
+const CPUID_1_EDX_HIDDEN: u32 = (1 << 7) | (1 << 12) | (1 << 14);
+const CPUID_1_ECX_HIDDEN: u32 = (1 << 5) | (1 << 6) | (1 << 15) | (1 << 21) | (1 << 24)
+    | (1 << 26) | (1 << 27) | (1 << 28);
+const CPUID_1_ECX_HYPERVISOR: u32 = 1 << 31;
+
+/// CPUID.7.0 features hidden from the guest: AVX2, INVPCID and AVX-512 in
+/// EBX; AVX-512, WAITPKG and RDPID in ECX; AVX-512 in EDX
+const CPUID_7_EBX_HIDDEN: u32 = (1 << 5) | (1 << 10) | (1 << 16) | (1 << 17) | (1 << 21)
+    | (1 << 26) | (1 << 27) | (1 << 28) | (1 << 30) | (1 << 31);
+const CPUID_7_ECX_HIDDEN: u32 = (1 << 1) | (1 << 5) | (1 << 6) | (1 << 11) | (1 << 12)
+    | (1 << 14) | (1 << 22);
+const CPUID_7_EDX_HIDDEN: u32 = (1 << 2) | (1 << 3) | (1 << 8) | (1 << 23);
+
+/// CPUID.80000001:EDX RDTSCP, which needs a control this backend leaves off
+const CPUID_80000001_EDX_RDTSCP: u32 = 1 << 27;
+
+/// ============================================================================
+/// VMX Instructions
+/// ============================================================================
+
+#[inline]
+unsafe fn vmread(field: u32) -> u64 {
+    let value: u64;
+    core::arch::asm!("vmread {}, {}", out(reg) value, in(reg) field as u64, options(nostack));
+    value
+}
+
+#[inline]
+unsafe fn vmwrite(field: u32, value: u64) {
+    core::arch::asm!("vmwrite {}, {}", in(reg) field as u64, in(reg) value, options(nostack));
+}
+
+/// Fails if the VMX instruction just run set CF or ZF
+macro_rules! vmx_op {
+    ($insn:literal, $paddr:expr) => {{
+        let paddr: PAddr = $paddr;
+        let failed: u8;
+        core::arch::asm!(
+            concat!($insn, " qword ptr [{}]"),
+            "setna {}",
+            in(reg) &paddr,
+            out(reg_byte) failed,
+            options(nostack)
+        );
+        if failed != 0 {
+            Err(RX_ERR_INTERNAL)
+        } else {
+            Ok(())
+        }
+    }};
+}
+
+unsafe fn vmxon(paddr: PAddr) -> Result {
+    vmx_op!("vmxon", paddr)
+}
+
+unsafe fn vmxoff() {
+    core::arch::asm!("vmxoff", options(nostack));
+}
+
+unsafe fn vmclear(paddr: PAddr) -> Result {
+    vmx_op!("vmclear", paddr)
+}
+
+unsafe fn vmptrld(paddr: PAddr) -> Result {
+    vmx_op!("vmptrld", paddr)
+}
+
+/// Drop the TLB entries tagged with `eptp`
+unsafe fn invept(eptp: u64) {
+    const SINGLE_CONTEXT: u64 = 1;
+    let descriptor: [u64; 2] = [eptp, 0];
+    core::arch::asm!(
+        "invept {}, [{}]",
+        in(reg) SINGLE_CONTEXT,
+        in(reg) &descriptor,
+        options(nostack)
+    );
+}
+
+#[inline]
+fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    unsafe { __cpuid_count(leaf, subleaf) }
+}
+
+/// ============================================================================
+/// Guest Entry and Exit
+/// ============================================================================
+
+/// Host registers kept across a run
+#[repr(C)]
+#[derive(Debug, Default)]
+struct HostRegs {
+    rip: u64,
+    rsp: u64,
+    rbx: u64,
+    rbp: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rflags: u64,
+}
+
+/// Guest registers the VMCS doesn't hold
+#[repr(C)]
+#[derive(Debug, Default)]
+struct GuestRegs {
+    rax: u64,
+    rcx: u64,
+    rdx: u64,
+    rbx: u64,
+    rbp: u64,
+    rsi: u64,
+    rdi: u64,
+    r8: u64,
+    r9: u64,
+    r10: u64,
+    r11: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    cr2: u64,
+}
+
+/// State [`vmx_enter`] and [`vmx_exit_entry`] share; the host RSP in the
+/// VMCS points at it
+#[repr(C)]
+#[derive(Debug, Default)]
+struct VmxState {
+    /// Bit 0 set once the VMCS is launched
+    resume: u64,
+    host: HostRegs,
+    guest: GuestRegs,
+}
+
+/// Enter the guest
+///
+/// Returns 0 after a VM exit, 1 if the entry failed.
+#[unsafe(naked)]
+unsafe extern "C" fn vmx_enter(state: *mut VmxState) -> u32 {
+    core::arch::naked_asm!(
+        // Taking the return address first leaves RSP as the caller's
+        "pop qword ptr [rdi + {hs_rip}]",
+        "mov [rdi + {hs_rsp}], rsp",
+        "mov [rdi + {hs_rbx}], rbx",
+        "mov [rdi + {hs_rbp}], rbp",
+        "mov [rdi + {hs_r12}], r12",
+        "mov [rdi + {hs_r13}], r13",
+        "mov [rdi + {hs_r14}], r14",
+        "mov [rdi + {hs_r15}], r15",
+        "pushfq",
+        "pop qword ptr [rdi + {hs_rflags}]",
+
+        // The state stays in RSP, where a VM exit puts it too
+        "mov rsp, rdi",
+        "mov rax, [rsp + {gs_cr2}]",
+        "mov cr2, rax",
+        "mov rax, [rsp + {gs_rax}]",
+        "mov rcx, [rsp + {gs_rcx}]",
+        "mov rdx, [rsp + {gs_rdx}]",
+        "mov rbx, [rsp + {gs_rbx}]",
+        "mov rbp, [rsp + {gs_rbp}]",
+        "mov rsi, [rsp + {gs_rsi}]",
+        "mov rdi, [rsp + {gs_rdi}]",
+        "mov r8, [rsp + {gs_r8}]",
+        "mov r9, [rsp + {gs_r9}]",
+        "mov r10, [rsp + {gs_r10}]",
+        "mov r11, [rsp + {gs_r11}]",
+        "mov r12, [rsp + {gs_r12}]",
+        "mov r13, [rsp + {gs_r13}]",
+        "mov r14, [rsp + {gs_r14}]",
+        "mov r15, [rsp + {gs_r15}]",
+
+        "test byte ptr [rsp + {resume}], 1",
+        "jnz 2f",
+        "vmlaunch",
+        "jmp 3f",
+        "2:",
+        "vmresume",
+
+        // Only reached if the entry failed
+        "3:",
+        "mov rdi, rsp",
+        "mov rsp, [rdi + {hs_rsp}]",
+        "mov rbx, [rdi + {hs_rbx}]",
+        "mov rbp, [rdi + {hs_rbp}]",
+        "mov r12, [rdi + {hs_r12}]",
+        "mov r13, [rdi + {hs_r13}]",
+        "mov r14, [rdi + {hs_r14}]",
+        "mov r15, [rdi + {hs_r15}]",
+        "push qword ptr [rdi + {hs_rflags}]",
+        "popfq",
+        "push qword ptr [rdi + {hs_rip}]",
+        "mov eax, 1",
+        "ret",
+
+        resume = const core::mem::offset_of!(VmxState, resume),
+        hs_rip = const core::mem::offset_of!(VmxState, host.rip),
+        hs_rsp = const core::mem::offset_of!(VmxState, host.rsp),
+        hs_rbx = const core::mem::offset_of!(VmxState, host.rbx),
+        hs_rbp = const core::mem::offset_of!(VmxState, host.rbp),
+        hs_r12 = const core::mem::offset_of!(VmxState, host.r12),
+        hs_r13 = const core::mem::offset_of!(VmxState, host.r13),
+        hs_r14 = const core::mem::offset_of!(VmxState, host.r14),
+        hs_r15 = const core::mem::offset_of!(VmxState, host.r15),
+        hs_rflags = const core::mem::offset_of!(VmxState, host.rflags),
+        gs_rax = const core::mem::offset_of!(VmxState, guest.rax),
+        gs_rcx = const core::mem::offset_of!(VmxState, guest.rcx),
+        gs_rdx = const core::mem::offset_of!(VmxState, guest.rdx),
+        gs_rbx = const core::mem::offset_of!(VmxState, guest.rbx),
+        gs_rbp = const core::mem::offset_of!(VmxState, guest.rbp),
+        gs_rsi = const core::mem::offset_of!(VmxState, guest.rsi),
+        gs_rdi = const core::mem::offset_of!(VmxState, guest.rdi),
+        gs_r8 = const core::mem::offset_of!(VmxState, guest.r8),
+        gs_r9 = const core::mem::offset_of!(VmxState, guest.r9),
+        gs_r10 = const core::mem::offset_of!(VmxState, guest.r10),
+        gs_r11 = const core::mem::offset_of!(VmxState, guest.r11),
+        gs_r12 = const core::mem::offset_of!(VmxState, guest.r12),
+        gs_r13 = const core::mem::offset_of!(VmxState, guest.r13),
+        gs_r14 = const core::mem::offset_of!(VmxState, guest.r14),
+        gs_r15 = const core::mem::offset_of!(VmxState, guest.r15),
+        gs_cr2 = const core::mem::offset_of!(VmxState, guest.cr2),
+    );
+}
+
+/// Host RIP of the VMCS: save the guest registers and return 0 from
+/// [`vmx_enter`]
+#[unsafe(naked)]
+unsafe extern "C" fn vmx_exit_entry() {
+    core::arch::naked_asm!(
+        "mov [rsp + {gs_rax}], rax",
+        "mov [rsp + {gs_rcx}], rcx",
+        "mov [rsp + {gs_rdx}], rdx",
+        "mov [rsp + {gs_rbx}], rbx",
+        "mov [rsp + {gs_rbp}], rbp",
+        "mov [rsp + {gs_rsi}], rsi",
+        "mov [rsp + {gs_rdi}], rdi",
+        "mov [rsp + {gs_r8}], r8",
+        "mov [rsp + {gs_r9}], r9",
+        "mov [rsp + {gs_r10}], r10",
+        "mov [rsp + {gs_r11}], r11",
+        "mov [rsp + {gs_r12}], r12",
+        "mov [rsp + {gs_r13}], r13",
+        "mov [rsp + {gs_r14}], r14",
+        "mov [rsp + {gs_r15}], r15",
+        "mov rax, cr2",
+        "mov [rsp + {gs_cr2}], rax",
+
+        "mov rdi, rsp",
+        "mov rsp, [rdi + {hs_rsp}]",
+        "mov rbx, [rdi + {hs_rbx}]",
+        "mov rbp, [rdi + {hs_rbp}]",
+        "mov r12, [rdi + {hs_r12}]",
+        "mov r13, [rdi + {hs_r13}]",
+        "mov r14, [rdi + {hs_r14}]",
+        "mov r15, [rdi + {hs_r15}]",
+        "push qword ptr [rdi + {hs_rflags}]",
+        "popfq",
+        "push qword ptr [rdi + {hs_rip}]",
+        "xor eax, eax",
+        "ret",
+
+        hs_rsp = const core::mem::offset_of!(VmxState, host.rsp),
+        hs_rbx = const core::mem::offset_of!(VmxState, host.rbx),
+        hs_rbp = const core::mem::offset_of!(VmxState, host.rbp),
+        hs_r12 = const core::mem::offset_of!(VmxState, host.r12),
+        hs_r13 = const core::mem::offset_of!(VmxState, host.r13),
+        hs_r14 = const core::mem::offset_of!(VmxState, host.r14),
+        hs_r15 = const core::mem::offset_of!(VmxState, host.r15),
+        hs_rflags = const core::mem::offset_of!(VmxState, host.rflags),
+        hs_rip = const core::mem::offset_of!(VmxState, host.rip),
+        gs_rax = const core::mem::offset_of!(VmxState, guest.rax),
+        gs_rcx = const core::mem::offset_of!(VmxState, guest.rcx),
+        gs_rdx = const core::mem::offset_of!(VmxState, guest.rdx),
+        gs_rbx = const core::mem::offset_of!(VmxState, guest.rbx),
+        gs_rbp = const core::mem::offset_of!(VmxState, guest.rbp),
+        gs_rsi = const core::mem::offset_of!(VmxState, guest.rsi),
+        gs_rdi = const core::mem::offset_of!(VmxState, guest.rdi),
+        gs_r8 = const core::mem::offset_of!(VmxState, guest.r8),
+        gs_r9 = const core::mem::offset_of!(VmxState, guest.r9),
+        gs_r10 = const core::mem::offset_of!(VmxState, guest.r10),
+        gs_r11 = const core::mem::offset_of!(VmxState, guest.r11),
+        gs_r12 = const core::mem::offset_of!(VmxState, guest.r12),
+        gs_r13 = const core::mem::offset_of!(VmxState, guest.r13),
+        gs_r14 = const core::mem::offset_of!(VmxState, guest.r14),
+        gs_r15 = const core::mem::offset_of!(VmxState, guest.r15),
+        gs_cr2 = const core::mem::offset_of!(VmxState, guest.cr2),
+    );
+}
+
+/// ============================================================================
+/// Capabilities
+/// ============================================================================
+
+/// Whether `cap` lets every bit of `bits` be set
+fn allowed(cap: u64, bits: u32) -> bool {
+    (cap >> 32) as u32 & bits == bits
+}
+
+/// Controls with `want` set, plus the bits `cap` requires
+///
+/// A capability MSR holds the bits that must be 1 in its low half and the
+/// bits that may be 1 in its high half.
+fn adjust_controls(cap: u64, want: u32) -> Result<u32> {
+    if !allowed(cap, want) {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    Ok(want | cap as u32)
+}
+
+/// Whether `value` has the bits a VMX fixed-bit MSR pair requires
+fn fixed_bits_valid(value: u64, fixed0: u64, fixed1: u64) -> bool {
+    value & fixed0 == fixed0 && value & !fixed1 == 0
+}
+
+/// Whether `addr` is a canonical 48-bit address
+fn is_canonical(addr: u64) -> bool {
+    ((addr << 16) as i64 >> 16) as u64 == addr
+}
+
+/// Execution controls and settings probed from the CPU
+#[derive(Debug, Clone, Copy)]
+struct Controls {
+    /// VMCS revision identifier
+    revision: u32,
+    pin: u32,
+    primary: u32,
+    secondary: u32,
+    exit: u32,
+    entry: u32,
+
+    /// Preemption timer rate as a shift of the TSC, if there is a timer
+    timer_shift: Option<u32>,
+}
+
+impl Controls {
+    /// Controls for the current CPU
+    ///
+    /// Fails with `RX_ERR_NOT_SUPPORTED` without VMX, EPT, or the controls
+    /// the backend depends on.
+    fn probe() -> Result<Self> {
+        if cpuid(1, 0).ecx & CPUID_1_ECX_VMX == 0 {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        unsafe {
+            let feature = read_msr(vmx_msr::IA32_FEATURE_CONTROL);
+            if feature & FEATURE_CONTROL_LOCK != 0 && feature & FEATURE_CONTROL_VMXON_OUTSIDE_SMX == 0 {
+                return Err(RX_ERR_NOT_SUPPORTED);
+            }
+
+            let basic = read_msr(vmx_msr::IA32_VMX_BASIC);
+            let region_size = (basic >> 32) & 0x1FFF;
+            if basic & BASIC_TRUE_CONTROLS == 0
+                || (basic >> BASIC_MEMORY_TYPE_SHIFT) & 0xF != MEMORY_TYPE_WB
+                || region_size > PAGE_SIZE
+            {
+                return Err(RX_ERR_NOT_SUPPORTED);
+            }
+
+            let ept = EPT_CAP_4_LEVEL | EPT_CAP_WB | EPT_CAP_INVEPT | EPT_CAP_INVEPT_SINGLE;
+            if read_msr(vmx_msr::IA32_VMX_EPT_VPID_CAP) & ept != ept {
+                return Err(RX_ERR_NOT_SUPPORTED);
+            }
+
+            let pin_cap = read_msr(vmx_msr::IA32_VMX_TRUE_PINBASED_CTLS);
+            let mut pin = PIN_EXTERNAL_INTERRUPT_EXITING | PIN_NMI_EXITING;
+            let timer = allowed(pin_cap, PIN_PREEMPTION_TIMER);
+            if timer {
+                pin |= PIN_PREEMPTION_TIMER;
+            }
+
+            let primary_cap = read_msr(vmx_msr::IA32_VMX_TRUE_PROCBASED_CTLS);
+            if !allowed(primary_cap, PROC_INTERRUPT_WINDOW_EXITING) {
+                return Err(RX_ERR_NOT_SUPPORTED);
+            }
+            let primary = adjust_controls(
+                primary_cap,
+                PROC_HLT_EXITING
+                    | PROC_RDPMC_EXITING
+                    | PROC_TPR_SHADOW
+                    | PROC_MOV_DR_EXITING
+                    | PROC_UNCONDITIONAL_IO_EXITING
+                    | PROC_SECONDARY_CONTROLS,
+            )?;
+
+            Ok(Self {
+                revision: basic as u32 & 0x7FFF_FFFF,
+                pin: adjust_controls(pin_cap, pin)?,
+                primary,
+                secondary: adjust_controls(read_msr(vmx_msr::IA32_VMX_PROCBASED_CTLS2), PROC2_EPT)?,
+                exit: adjust_controls(
+                    read_msr(vmx_msr::IA32_VMX_TRUE_EXIT_CTLS),
+                    EXIT_HOST_64BIT | EXIT_SAVE_EFER | EXIT_LOAD_EFER,
+                )?,
+                entry: adjust_controls(
+                    read_msr(vmx_msr::IA32_VMX_TRUE_ENTRY_CTLS),
+                    ENTRY_IA32E_GUEST | ENTRY_LOAD_EFER,
+                )?,
+                timer_shift: timer.then(|| read_msr(vmx_msr::IA32_VMX_MISC) as u32 & 0x1F),
+            })
+        }
+    }
+}
+
+/// ============================================================================
+/// Guests
+/// ============================================================================
+
+/// A zeroed page from the PMM, freed on drop
+struct Page(PAddr);
+
+impl Page {
+    fn alloc() -> Result<Self> {
+        Ok(Self(pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?))
+    }
+
+    fn ptr<T>(&self) -> *mut T {
+        pmm::paddr_to_vaddr(self.0) as *mut T
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        let _ = pmm::pmm_free_page(self.0);
+    }
+}
+
+/// Run counters of a guest's vCPUs, odd while the vCPU is in the guest
+type RunCounters = [AtomicU64; MAX_VCPUS];
+
+/// Guest-physical memory of a VMX guest
+pub struct VmxGuest {
+    /// Extended page tables
+    ept: IoPageTable,
+
+    /// Shared with the guest's vCPUs
+    running: Arc<RunCounters>,
+}
+
+impl VmxGuest {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            ept: IoPageTable::new(PteFormat::Ept)?,
+            running: Arc::new([const { AtomicU64::new(0) }; MAX_VCPUS]),
+        })
+    }
+
+    /// EPT pointer: write-back tables with a four-level walk
+    fn eptp(&self) -> u64 {
+        self.ept.root() | MEMORY_TYPE_WB | (3 << 3)
+    }
+
+    /// Map the guest page at `addr`
+    ///
+    /// Another vCPU may have mapped it after faulting on it too.
+    pub fn map_page(&mut self, addr: u64, paddr: PAddr, writable: bool) -> Result {
+        let mut perms = perm::READ | perm::EXECUTE;
+        if writable {
+            perms |= perm::WRITE;
+        }
+        match self.ept.map_page(addr, paddr, perms) {
+            Err(RX_ERR_ALREADY_EXISTS) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Unmap `[addr, addr + len)` and wait until no vCPU can still use it
+    pub fn unmap(&mut self, addr: u64, len: usize) {
+        for page in (addr..addr + len as u64).step_by(PAGE_SIZE as usize) {
+            self.ept.unmap_page(page);
+        }
+
+        // vCPUs entering from now on flush their translations first; wait
+        // for the ones already in the guest to leave it
+        fence(Ordering::SeqCst);
+        for counter in self.running.iter() {
+            let seen = counter.load(Ordering::SeqCst);
+            if seen & 1 != 0 {
+                while counter.load(Ordering::SeqCst) == seen {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+}
+
+/// ============================================================================
+/// vCPUs
+/// ============================================================================
+
+/// An event to deliver on the next entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Event {
+    /// Interruption information
+    info: u32,
+    error_code: u32,
+    instruction_len: u32,
+}
+
+/// A decoded `MOV` between a register or immediate and memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Mov {
+    /// Instruction length
+    len: u8,
+
+    /// Bytes accessed
+    size: u8,
+
+    /// Whether memory is written
+    write: bool,
+
+    /// Register read into or written from
+    reg: u8,
+
+    /// Immediate written instead of `reg`
+    imm: Option<u64>,
+}
+
+/// Decode a `MOV` to or from memory in 64-bit mode
+///
+/// Handles opcodes 88, 89, 8A, 8B, C6 and C7 with operand-size, address-
+/// size, segment and REX prefixes. Returns `None` for anything else,
+/// including moves to the legacy high-byte registers.
+fn decode_mov(bytes: &[u8]) -> Option<Mov> {
+    let mut pos = 0;
+    let mut operand_16 = false;
+    loop {
+        match *bytes.get(pos)? {
+            0x66 => operand_16 = true,
+            0x67 | 0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 => {}
+            _ => break,
+        }
+        pos += 1;
+    }
+
+    let mut rex = 0;
+    if bytes.get(pos)? & 0xF0 == 0x40 {
+        rex = bytes[pos];
+        pos += 1;
+    }
+    let rex_w = rex & 0x8 != 0;
+    let rex_r = (rex & 0x4) << 1;
+
+    let opcode = *bytes.get(pos)?;
+    pos += 1;
+    let full_size = if rex_w {
+        8
+    } else if operand_16 {
+        2
+    } else {
+        4
+    };
+    let (size, write, has_imm) = match opcode {
+        0x88 => (1, true, false),
+        0x89 => (full_size, true, false),
+        0x8A => (1, false, false),
+        0x8B => (full_size, false, false),
+        0xC6 => (1, true, true),
+        0xC7 => (full_size, true, true),
+        _ => return None,
+    };
+
+    let modrm = *bytes.get(pos)?;
+    pos += 1;
+    let mode = modrm >> 6;
+    let reg_field = (modrm >> 3) & 7;
+    let rm = modrm & 7;
+    if mode == 3 || (has_imm && reg_field != 0) {
+        return None;
+    }
+    let reg = reg_field | rex_r;
+    if size == 1 && rex == 0 && (4..8).contains(&reg) && !has_imm {
+        return None;
+    }
+
+    if rm == 4 {
+        let sib = *bytes.get(pos)?;
+        pos += 1;
+        if mode == 0 && sib & 7 == 5 {
+            pos += 4;
+        }
+    }
+    pos += match mode {
+        0 if rm == 5 => 4,
+        1 => 1,
+        2 => 4,
+        _ => 0,
+    };
+
+    let imm = if has_imm {
+        let imm_len = size.min(4) as usize;
+        let raw = bytes.get(pos..pos + imm_len)?;
+        pos += imm_len;
+        let mut value = 0u64;
+        for (i, &byte) in raw.iter().enumerate() {
+            value |= (byte as u64) << (8 * i);
+        }
+        if size == 8 {
+            // imm32, sign-extended
+            value = value as u32 as i32 as i64 as u64;
+        }
+        Some(value)
+    } else {
+        None
+    };
+
+    if pos > bytes.len() || pos > MAX_INSTRUCTION_LEN {
+        return None;
+    }
+    Some(Mov {
+        len: pos as u8,
+        size,
+        write,
+        reg: if has_imm { 0 } else { reg },
+        imm,
+    })
+}
+
+/// Mask of the low `size` bytes
+fn size_mask(size: u8) -> u64 {
+    if size >= 8 {
+        u64::MAX
+    } else {
+        (1u64 << (8 * size)) - 1
+    }
+}
+
+/// Decode an I/O instruction exit
+///
+/// String and `REP` forms are left to the caller, as `None`.
+fn io_exit(qualification: u64, rax: u64) -> Option<GuestExit> {
+    const STRING: u64 = 1 << 4;
+    const REP: u64 = 1 << 5;
+    if qualification & (STRING | REP) != 0 {
+        return None;
+    }
+    let access_size = (qualification & 7) as u8 + 1;
+    Some(GuestExit::Io {
+        port: (qualification >> 16) as u16,
+        access_size,
+        input: qualification & (1 << 3) != 0,
+        data: (rax & size_mask(access_size)) as u32,
+    })
+}
+
+/// Keep the RFLAGS bits a guest may hold
+fn sanitize_rflags(value: u64) -> u64 {
+    value & RFLAGS_VALID | RFLAGS_FIXED
+}
+
+/// VMX state of one vCPU
+pub struct VmxVcpu {
+    /// vCPU number within the guest
+    index: u16,
+
+    controls: Controls,
+
+    /// Guest's EPT pointer
+    eptp: u64,
+
+    /// Guest's run counters; this vCPU's is at `index`
+    running: Arc<RunCounters>,
+
+    vmxon: Page,
+    vmcs: Page,
+
+    /// Virtual-APIC page, which holds the guest's TPR
+    virtual_apic: Page,
+
+    /// FPU save areas of the guest, and of the host while the guest runs
+    guest_fpu: Page,
+    host_fpu: Page,
+
+    /// Whether the VMCS guest state and controls are written
+    initialized: bool,
+
+    /// Registers kept outside the VMCS
+    state: VmxState,
+
+    /// Guest registers kept in the VMCS, copied out after every exit
+    rip: u64,
+    rsp: u64,
+    rflags: u64,
+    cr3: u64,
+    interruptibility: u32,
+
+    /// Guest values of `SWAPPED_MSRS`
+    msrs: [u64; SWAPPED_MSRS.len()],
+
+    /// Whether the guest ran 64-bit code at the last EPT violation
+    long_mode: bool,
+
+    /// Virtual interrupt waiting for the guest to take interrupts
+    pending: Option<u8>,
+
+    /// Exception to raise, or an event the last exit interrupted
+    event: Option<Event>,
+
+    /// Length of the instruction `skip_instruction` steps over
+    skip_len: u64,
+}
+
+impl VmxVcpu {
+    /// Create vCPU `index` of `guest`, starting at `entry`
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_NOT_SUPPORTED` - no VMX with EPT on this CPU
+    /// - `RX_ERR_INVALID_ARGS` - `entry` isn't canonical
+    /// - `RX_ERR_NO_MEMORY` - no pages for the VMCS or save areas
+    pub fn new(guest: &VmxGuest, index: u16, entry: u64) -> Result<Self> {
+        let controls = Controls::probe()?;
+        if !is_canonical(entry) {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if fpu::x86_fpu_state_size() > PAGE_SIZE as usize {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        let vmxon = Page::alloc()?;
+        let vmcs = Page::alloc()?;
+        let guest_fpu = Page::alloc()?;
+        unsafe {
+            vmxon.ptr::<u32>().write(controls.revision);
+            vmcs.ptr::<u32>().write(controls.revision);
+            fpu::x86_fpu_init_state(guest_fpu.ptr());
+        }
+
+        Ok(Self {
+            index,
+            controls,
+            eptp: guest.eptp(),
+            running: guest.running.clone(),
+            vmxon,
+            vmcs,
+            virtual_apic: Page::alloc()?,
+            guest_fpu,
+            host_fpu: Page::alloc()?,
+            initialized: false,
+            state: VmxState::default(),
+            rip: entry,
+            rsp: 0,
+            rflags: RFLAGS_FIXED,
+            cr3: 0,
+            interruptibility: 0,
+            msrs: [0; SWAPPED_MSRS.len()],
+            long_mode: true,
+            pending: None,
+            event: None,
+            skip_len: 0,
+        })
+    }
+
+    /// Run the guest until an exit the kernel doesn't handle here
+    ///
+    /// Interrupts are taken from `next_interrupt` whenever none is waiting
+    /// for the guest to accept it.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_BAD_STATE` - the guest state is invalid for VM entry
+    /// - `RX_ERR_NOT_SUPPORTED` - the CPU can't enter VMX operation
+    /// - `RX_ERR_INTERNAL` - a VMX instruction failed
+    pub fn enter(&mut self, mut next_interrupt: impl FnMut() -> Option<u32>) -> Result<GuestExit> {
+        self.skip_len = 0;
+        let state = arch_interrupt_save();
+        let result = unsafe { self.run_vmx(&mut next_interrupt) };
+        unsafe { arch_interrupt_restore(state) };
+        result
+    }
+
+    /// Turn VMX on around [`Self::run_loaded`]
+    unsafe fn run_vmx(&mut self, next_interrupt: &mut dyn FnMut() -> Option<u32>) -> Result<GuestExit> {
+        let cr4 = x86_get_cr4();
+        let vmx_cr4 = cr4 | CR4_VMXE;
+        let cr0_valid = fixed_bits_valid(
+            x86_get_cr0(),
+            read_msr(vmx_msr::IA32_VMX_CR0_FIXED0),
+            read_msr(vmx_msr::IA32_VMX_CR0_FIXED1),
+        );
+        let cr4_valid = fixed_bits_valid(
+            vmx_cr4,
+            read_msr(vmx_msr::IA32_VMX_CR4_FIXED0),
+            read_msr(vmx_msr::IA32_VMX_CR4_FIXED1),
+        );
+        if !cr0_valid || !cr4_valid {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        // Firmware may leave the control unlocked; VMXON faults until it
+        // is locked with VMX allowed
+        let feature = read_msr(vmx_msr::IA32_FEATURE_CONTROL);
+        if feature & FEATURE_CONTROL_LOCK == 0 {
+            write_msr(
+                vmx_msr::IA32_FEATURE_CONTROL,
+                feature | FEATURE_CONTROL_LOCK | FEATURE_CONTROL_VMXON_OUTSIDE_SMX,
+            );
+        }
+
+        x86_set_cr4(vmx_cr4);
+        if let Err(err) = vmxon(self.vmxon.0) {
+            x86_set_cr4(cr4);
+            return Err(err);
+        }
+
+        let mut nmi = false;
+        let result = match vmclear(self.vmcs.0).and_then(|_| vmptrld(self.vmcs.0)) {
+            Ok(()) => {
+                let result = self.run_loaded(vmx_cr4, &mut nmi, next_interrupt);
+                let _ = vmclear(self.vmcs.0);
+                result
+            }
+            Err(err) => Err(err),
+        };
+
+        vmxoff();
+        x86_set_cr4(cr4);
+
+        // The NMI that made the guest exit is still the host's
+        if nmi {
+            core::arch::asm!("int 2", options(nomem, nostack));
+        }
+        result
+    }
+
+    /// Load the guest's FPU and MSRs, run it, and put the host's back
+    unsafe fn run_loaded(
+        &mut self,
+        cr4: u64,
+        nmi: &mut bool,
+        next_interrupt: &mut dyn FnMut() -> Option<u32>,
+    ) -> Result<GuestExit> {
+        if !self.initialized {
+            self.init_vmcs();
+            self.initialized = true;
+        }
+
+        // The host registers are saved whoever they belong to, so lazy FPU
+        // switching finds them as it left them
+        let fpu_disabled = !fpu::x86_fpu_enabled();
+        fpu::x86_fpu_enable();
+        fpu::x86_fpu_save(self.host_fpu.ptr());
+        fpu::x86_fpu_restore(self.guest_fpu.ptr());
+
+        let mut host_msrs = [0u64; SWAPPED_MSRS.len()];
+        for (i, &index) in SWAPPED_MSRS.iter().enumerate() {
+            host_msrs[i] = read_msr(index);
+            write_msr(index, self.msrs[i]);
+        }
+
+        let mut gdtr = GdtPointer { limit: 0, base: 0 };
+        let mut idtr = IdtPointer { limit: 0, base: 0 };
+        core::arch::asm!("sgdt [{}]", in(reg) &mut gdtr, options(nostack));
+        core::arch::asm!("sidt [{}]", in(reg) &mut idtr, options(nostack));
+        let dr7: u64;
+        let ds: u16;
+        let es: u16;
+        core::arch::asm!("mov {}, dr7", out(reg) dr7, options(nomem, nostack));
+        core::arch::asm!("mov {:x}, ds", out(reg) ds, options(nomem, nostack));
+        core::arch::asm!("mov {:x}, es", out(reg) es, options(nomem, nostack));
+
+        self.write_host_state(cr4, gdtr.base, idtr.base);
+
+        let running = self.running.clone();
+        let counter = &running[self.index as usize];
+        counter.fetch_add(1, Ordering::SeqCst);
+        invept(self.eptp);
+
+        self.state.resume = 0;
+        let result = loop {
+            self.prepare_entry(next_interrupt);
+            if vmx_enter(&mut self.state) != 0 {
+                log_error!("vmx: entry failed, error {}", vmread(vmcs::VM_INSTRUCTION_ERROR));
+                break Err(RX_ERR_INTERNAL);
+            }
+            self.state.resume = 1;
+
+            self.rip = vmread(vmcs::GUEST_RIP);
+            self.rsp = vmread(vmcs::GUEST_RSP);
+            self.rflags = vmread(vmcs::GUEST_RFLAGS);
+            self.cr3 = vmread(vmcs::GUEST_CR3);
+            self.interruptibility = vmread(vmcs::GUEST_INTERRUPTIBILITY) as u32;
+
+            match self.handle_exit(nmi, next_interrupt) {
+                Ok(Some(exit)) => break Ok(exit),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+        };
+
+        counter.fetch_add(1, Ordering::SeqCst);
+
+        // A VM exit leaves the descriptor table limits at 0xFFFF, TR
+        // without its I/O bitmap, and DR7 cleared
+        descriptor::gdt_load(&gdtr);
+        descriptor::idt_load(&idtr);
+        descriptor::x86_reload_tss(percpu::current_cpu_num());
+        core::arch::asm!("mov ds, {:x}", in(reg) ds, options(nomem, nostack));
+        core::arch::asm!("mov es, {:x}", in(reg) es, options(nomem, nostack));
+        core::arch::asm!("mov dr7, {}", in(reg) dr7, options(nomem, nostack));
+
+        for (i, &index) in SWAPPED_MSRS.iter().enumerate() {
+            self.msrs[i] = read_msr(index);
+            write_msr(index, host_msrs[i]);
+        }
+
+        fpu::x86_fpu_save(self.guest_fpu.ptr());
+        fpu::x86_fpu_restore(self.host_fpu.ptr());
+        if fpu_disabled {
+            fpu::x86_fpu_disable();
+        }
+
+        result
+    }
+
+    /// Write the controls and the initial guest state
+    unsafe fn init_vmcs(&mut self) {
+        let controls = self.controls;
+        vmwrite(vmcs::PIN_BASED_CONTROLS, controls.pin as u64);
+        vmwrite(vmcs::PROCESSOR_CONTROLS, controls.primary as u64);
+        vmwrite(vmcs::SECONDARY_CONTROLS, controls.secondary as u64);
+        vmwrite(vmcs::EXIT_CONTROLS, controls.exit as u64);
+        vmwrite(vmcs::ENTRY_CONTROLS, controls.entry as u64);
+        vmwrite(vmcs::EXCEPTION_BITMAP, 0);
+        vmwrite(vmcs::PAGE_FAULT_ERROR_MASK, 0);
+        vmwrite(vmcs::PAGE_FAULT_ERROR_MATCH, 0);
+        vmwrite(vmcs::CR3_TARGET_COUNT, 0);
+        vmwrite(vmcs::EXIT_MSR_STORE_COUNT, 0);
+        vmwrite(vmcs::EXIT_MSR_LOAD_COUNT, 0);
+        vmwrite(vmcs::ENTRY_MSR_LOAD_COUNT, 0);
+        vmwrite(vmcs::ENTRY_INTERRUPTION_INFO, 0);
+        vmwrite(vmcs::VIRTUAL_APIC_ADDRESS, self.virtual_apic.0);
+        vmwrite(vmcs::TPR_THRESHOLD, 0);
+        vmwrite(vmcs::EPT_POINTER, self.eptp);
+
+        // Bits VMX fixes can't change; the guest sees the values it
+        // started with
+        let cr0_fixed0 = read_msr(vmx_msr::IA32_VMX_CR0_FIXED0);
+        let cr0_fixed1 = read_msr(vmx_msr::IA32_VMX_CR0_FIXED1);
+        let cr0 = (CR0_PE | CR0_ET | CR0_NE | CR0_PG | cr0_fixed0) & cr0_fixed1;
+        vmwrite(vmcs::GUEST_CR0, cr0);
+        vmwrite(vmcs::CR0_GUEST_HOST_MASK, cr0_fixed0 | !cr0_fixed1 | CR0_PE | CR0_PG);
+        vmwrite(vmcs::CR0_READ_SHADOW, cr0);
+
+        let cr4_fixed0 = read_msr(vmx_msr::IA32_VMX_CR4_FIXED0);
+        let cr4_fixed1 = read_msr(vmx_msr::IA32_VMX_CR4_FIXED1);
+        let cr4 = (CR4_PAE | CR4_VMXE | cr4_fixed0) & cr4_fixed1;
+        vmwrite(vmcs::GUEST_CR4, cr4);
+        vmwrite(vmcs::CR4_GUEST_HOST_MASK, cr4_fixed0 | !cr4_fixed1 | CR4_VMXE);
+        vmwrite(vmcs::CR4_READ_SHADOW, cr4 & !CR4_VMXE);
+
+        vmwrite(vmcs::GUEST_IA32_EFER, efer::LME | efer::LMA);
+        for (i, &(selector, limit, access_rights)) in GUEST_SEGMENTS.iter().enumerate() {
+            let offset = 2 * i as u32;
+            vmwrite(vmcs::GUEST_ES_SELECTOR + offset, selector);
+            vmwrite(vmcs::GUEST_ES_LIMIT + offset, limit);
+            vmwrite(vmcs::GUEST_ES_ACCESS_RIGHTS + offset, access_rights);
+            vmwrite(vmcs::GUEST_ES_BASE + offset, 0);
+        }
+        vmwrite(vmcs::GUEST_GDTR_BASE, 0);
+        vmwrite(vmcs::GUEST_GDTR_LIMIT, 0xFFFF);
+        vmwrite(vmcs::GUEST_IDTR_BASE, 0);
+        vmwrite(vmcs::GUEST_IDTR_LIMIT, 0xFFFF);
+
+        vmwrite(vmcs::GUEST_DR7, DR7_INIT);
+        vmwrite(vmcs::GUEST_IA32_DEBUGCTL, 0);
+        vmwrite(vmcs::GUEST_PENDING_DEBUG_EXCEPTIONS, 0);
+        vmwrite(vmcs::GUEST_IA32_SYSENTER_CS, 0);
+        vmwrite(vmcs::GUEST_IA32_SYSENTER_ESP, 0);
+        vmwrite(vmcs::GUEST_IA32_SYSENTER_EIP, 0);
+        vmwrite(vmcs::GUEST_ACTIVITY_STATE, 0);
+        vmwrite(vmcs::VMCS_LINK_POINTER, u64::MAX);
+    }
+
+    /// Write the host state of the current CPU
+    unsafe fn write_host_state(&mut self, cr4: u64, gdtr_base: u64, idtr_base: u64) {
+        let cs: u16;
+        let ss: u16;
+        let tr: u16;
+        core::arch::asm!("mov {:x}, cs", out(reg) cs, options(nomem, nostack));
+        core::arch::asm!("mov {:x}, ss", out(reg) ss, options(nomem, nostack));
+        core::arch::asm!("str {:x}", out(reg) tr, options(nomem, nostack));
+
+        vmwrite(vmcs::HOST_CR0, x86_get_cr0());
+        vmwrite(vmcs::HOST_CR3, x86_get_cr3());
+        vmwrite(vmcs::HOST_CR4, cr4);
+        vmwrite(vmcs::HOST_CS_SELECTOR, cs as u64);
+        vmwrite(vmcs::HOST_SS_SELECTOR, ss as u64);
+        vmwrite(vmcs::HOST_DS_SELECTOR, 0);
+        vmwrite(vmcs::HOST_ES_SELECTOR, 0);
+        vmwrite(vmcs::HOST_FS_SELECTOR, 0);
+        vmwrite(vmcs::HOST_GS_SELECTOR, 0);
+        vmwrite(vmcs::HOST_TR_SELECTOR, tr as u64);
+        vmwrite(vmcs::HOST_FS_BASE, read_msr(msr::IA32_FS_BASE));
+        vmwrite(vmcs::HOST_GS_BASE, read_msr(msr::IA32_GS_BASE));
+        vmwrite(vmcs::HOST_TR_BASE, descriptor::get_tss() as *const _ as u64);
+        vmwrite(vmcs::HOST_GDTR_BASE, gdtr_base);
+        vmwrite(vmcs::HOST_IDTR_BASE, idtr_base);
+        vmwrite(vmcs::HOST_IA32_SYSENTER_CS, read_msr(vmx_msr::IA32_SYSENTER_CS));
+        vmwrite(vmcs::HOST_IA32_SYSENTER_ESP, read_msr(vmx_msr::IA32_SYSENTER_ESP));
+        vmwrite(vmcs::HOST_IA32_SYSENTER_EIP, read_msr(vmx_msr::IA32_SYSENTER_EIP));
+        vmwrite(vmcs::HOST_IA32_EFER, read_msr(msr::IA32_EFER));
+        vmwrite(vmcs::HOST_RSP, &mut self.state as *mut VmxState as u64);
+        vmwrite(vmcs::HOST_RIP, vmx_exit_entry as *const () as u64);
+    }
+
+    /// Whether an external interrupt can be delivered now
+    fn interruptible(&self) -> bool {
+        self.rflags & rflags::IF != 0
+            && self.interruptibility & (BLOCKING_BY_STI | BLOCKING_BY_MOV_SS) == 0
+    }
+
+    /// Write the guest registers and the event to deliver
+    unsafe fn prepare_entry(&mut self, next_interrupt: &mut dyn FnMut() -> Option<u32>) {
+        if self.rflags & rflags::IF == 0 {
+            self.interruptibility &= !BLOCKING_BY_STI;
+        }
+        vmwrite(vmcs::GUEST_RIP, self.rip);
+        vmwrite(vmcs::GUEST_RSP, self.rsp);
+        vmwrite(vmcs::GUEST_RFLAGS, self.rflags);
+        vmwrite(vmcs::GUEST_CR3, self.cr3);
+        vmwrite(vmcs::GUEST_INTERRUPTIBILITY, self.interruptibility as u64);
+
+        if self.pending.is_none() {
+            self.pending = next_interrupt().map(|vector| vector as u8);
+        }
+        if let Some(event) = self.event.take() {
+            vmwrite(vmcs::ENTRY_INTERRUPTION_INFO, event.info as u64);
+            vmwrite(vmcs::ENTRY_EXCEPTION_ERROR_CODE, event.error_code as u64);
+            vmwrite(vmcs::ENTRY_INSTRUCTION_LENGTH, event.instruction_len as u64);
+        } else if let Some(vector) = self.pending.filter(|_| self.interruptible()) {
+            vmwrite(vmcs::ENTRY_INTERRUPTION_INFO, (vector as u32 | INTERRUPTION_VALID) as u64);
+            self.pending = None;
+        }
+
+        // Exit as soon as the guest can take what is still waiting
+        let mut primary = self.controls.primary;
+        if self.pending.is_some() {
+            primary |= PROC_INTERRUPT_WINDOW_EXITING;
+        }
+        vmwrite(vmcs::PROCESSOR_CONTROLS, primary as u64);
+
+        if let Some(shift) = self.controls.timer_shift {
+            vmwrite(vmcs::PREEMPTION_TIMER_VALUE, SLICE_TSC_TICKS >> shift);
+        }
+    }
+
+    /// Handle the last exit
+    ///
+    /// Returns `None` to enter the guest again at once.
+    unsafe fn handle_exit(
+        &mut self,
+        nmi: &mut bool,
+        next_interrupt: &mut dyn FnMut() -> Option<u32>,
+    ) -> Result<Option<GuestExit>> {
+        let reason = vmread(vmcs::EXIT_REASON) as u32;
+        if reason & exit_reason::ENTRY_FAILURE != 0 {
+            log_error!("vmx: invalid guest state, exit reason {:#x}", reason);
+            return Err(RX_ERR_BAD_STATE);
+        }
+
+        // An event cut short by the exit is delivered again next entry
+        let vectoring = vmread(vmcs::IDT_VECTORING_INFO) as u32;
+        if vectoring & INTERRUPTION_VALID != 0 {
+            self.event = Some(Event {
+                info: vectoring & (INTERRUPTION_VALID | 0xFFF),
+                error_code: vmread(vmcs::IDT_VECTORING_ERROR_CODE) as u32,
+                instruction_len: vmread(vmcs::EXIT_INSTRUCTION_LENGTH) as u32,
+            });
+        }
+
+        let qualification = vmread(vmcs::EXIT_QUALIFICATION);
+        let instruction_len = vmread(vmcs::EXIT_INSTRUCTION_LENGTH);
+        let reason = reason & 0xFFFF;
+        let exit = match reason {
+            exit_reason::EXCEPTION_OR_NMI => {
+                let info = vmread(vmcs::EXIT_INTERRUPTION_INFO) as u32;
+                if (info >> INTERRUPTION_TYPE_SHIFT) & INTERRUPTION_TYPE_MASK == INTERRUPTION_TYPE_NMI {
+                    *nmi = true;
+                    Some(GuestExit::Interrupt)
+                } else {
+                    Some(GuestExit::Unhandled(reason))
+                }
+            }
+            exit_reason::EXTERNAL_INTERRUPT | exit_reason::PREEMPTION_TIMER => Some(GuestExit::Interrupt),
+            exit_reason::TRIPLE_FAULT => Some(GuestExit::Shutdown),
+            exit_reason::INTERRUPT_WINDOW => None,
+            exit_reason::CPUID => {
+                self.emulate_cpuid();
+                self.advance(instruction_len);
+                None
+            }
+            exit_reason::HLT => {
+                self.advance(instruction_len);
+                if self.pending.is_none() {
+                    self.pending = next_interrupt().map(|vector| vector as u8);
+                }
+                if self.pending.is_some() && self.interruptible() {
+                    None
+                } else {
+                    Some(GuestExit::Wait)
+                }
+            }
+            exit_reason::MOV_DR => {
+                // Debug registers read as 0 and ignore writes
+                const MOV_FROM_DR: u64 = 1 << 4;
+                if qualification & MOV_FROM_DR != 0 {
+                    self.set_reg(((qualification >> 8) & 0xF) as u8, 0);
+                }
+                self.advance(instruction_len);
+                None
+            }
+            exit_reason::IO => match io_exit(qualification, self.state.guest.rax) {
+                Some(exit) => {
+                    self.skip_len = instruction_len;
+                    Some(exit)
+                }
+                None => Some(GuestExit::Unhandled(reason)),
+            },
+            exit_reason::RDMSR => {
+                self.emulate_rdmsr(instruction_len);
+                None
+            }
+            exit_reason::WRMSR => {
+                self.emulate_wrmsr(instruction_len);
+                None
+            }
+            exit_reason::CONTROL_REGISTER | exit_reason::RDPMC => {
+                self.raise_exception(EXCEPTION_GP, Some(0));
+                None
+            }
+            exit_reason::GETSEC
+            | exit_reason::INVD
+            | exit_reason::VMCALL..=exit_reason::VMXON
+            | exit_reason::INVEPT
+            | exit_reason::INVVPID
+            | exit_reason::XSETBV => {
+                self.raise_exception(EXCEPTION_UD, None);
+                None
+            }
+            exit_reason::EPT_VIOLATION => {
+                const WRITE: u64 = 1 << 1;
+                self.long_mode = vmread(vmcs::GUEST_CS_ACCESS_RIGHTS) & ACCESS_RIGHTS_LONG_MODE != 0;
+                Some(GuestExit::PageFault {
+                    addr: vmread(vmcs::GUEST_PHYSICAL_ADDRESS),
+                    write: qualification & WRITE != 0,
+                })
+            }
+            _ => Some(GuestExit::Unhandled(reason)),
+        };
+        Ok(exit)
+    }
+
+    /// Raise exception `vector` in the guest on the next entry
+    fn raise_exception(&mut self, vector: u8, error_code: Option<u32>) {
+        let mut info = vector as u32
+            | INTERRUPTION_TYPE_HARDWARE_EXCEPTION << INTERRUPTION_TYPE_SHIFT
+            | INTERRUPTION_VALID;
+        if error_code.is_some() {
+            info |= INTERRUPTION_DELIVER_ERROR_CODE;
+        }
+        self.event = Some(Event {
+            info,
+            error_code: error_code.unwrap_or(0),
+            instruction_len: 0,
+        });
+    }
+
+    /// Step over an instruction `len` bytes long
+    fn advance(&mut self, len: u64) {
+        self.skip_len = len;
+        self.skip_instruction();
+    }
+
+    /// Step over the instruction that caused the last exit
+    ///
+    /// Does nothing if its length isn't known, as after an access that
+    /// [`Self::decode_access`] couldn't decode.
+    pub fn skip_instruction(&mut self) {
+        if self.skip_len == 0 {
+            return;
+        }
+        self.rip = self.rip.wrapping_add(self.skip_len);
+        self.interruptibility &= !(BLOCKING_BY_STI | BLOCKING_BY_MOV_SS);
+        self.skip_len = 0;
+    }
+
+    /// General register `index`, in instruction encoding order
+    fn reg(&self, index: u8) -> u64 {
+        let regs = &self.state.guest;
+        match index {
+            0 => regs.rax,
+            1 => regs.rcx,
+            2 => regs.rdx,
+            3 => regs.rbx,
+            4 => self.rsp,
+            5 => regs.rbp,
+            6 => regs.rsi,
+            7 => regs.rdi,
+            8 => regs.r8,
+            9 => regs.r9,
+            10 => regs.r10,
+            11 => regs.r11,
+            12 => regs.r12,
+            13 => regs.r13,
+            14 => regs.r14,
+            _ => regs.r15,
+        }
+    }
+
+    fn set_reg(&mut self, index: u8, value: u64) {
+        let regs = &mut self.state.guest;
+        let reg = match index {
+            0 => &mut regs.rax,
+            1 => &mut regs.rcx,
+            2 => &mut regs.rdx,
+            3 => &mut regs.rbx,
+            4 => &mut self.rsp,
+            5 => &mut regs.rbp,
+            6 => &mut regs.rsi,
+            7 => &mut regs.rdi,
+            8 => &mut regs.r8,
+            9 => &mut regs.r9,
+            10 => &mut regs.r10,
+            11 => &mut regs.r11,
+            12 => &mut regs.r12,
+            13 => &mut regs.r13,
+            14 => &mut regs.r14,
+            _ => &mut regs.r15,
+        };
+        *reg = value;
+    }
+
+    /// Run CPUID for the guest, hiding what it can't use
+    fn emulate_cpuid(&mut self) {
+        let leaf = self.state.guest.rax as u32;
+        let subleaf = self.state.guest.rcx as u32;
+        let mut result = match leaf {
+            CPUID_PERFMON | CPUID_XSAVE | CPUID_HYPERVISOR_FIRST..=CPUID_HYPERVISOR_LAST => {
+                CpuidResult { eax: 0, ebx: 0, ecx: 0, edx: 0 }
+            }
+            _ => cpuid(leaf, subleaf),
+        };
+        match leaf {
+            1 => {
+                result.ecx = result.ecx & !CPUID_1_ECX_HIDDEN | CPUID_1_ECX_HYPERVISOR;
+                result.edx &= !CPUID_1_EDX_HIDDEN;
+                // The vCPU number is the initial APIC ID
+                result.ebx = result.ebx & 0x00FF_FFFF | (self.index as u32) << 24;
+            }
+            7 => {
+                result.ebx &= !CPUID_7_EBX_HIDDEN;
+                result.ecx &= !CPUID_7_ECX_HIDDEN;
+                result.edx &= !CPUID_7_EDX_HIDDEN;
+            }
+            0x8000_0001 => result.edx &= !CPUID_80000001_EDX_RDTSCP,
+            _ => {}
+        }
+
+        let regs = &mut self.state.guest;
+        regs.rax = result.eax as u64;
+        regs.rbx = result.ebx as u64;
+        regs.rcx = result.ecx as u64;
+        regs.rdx = result.edx as u64;
+    }
+
+    /// Read the guest's copy of MSR `index`
+    unsafe fn guest_msr(&self, index: u32) -> Option<u64> {
+        if let Some(i) = SWAPPED_MSRS.iter().position(|&swapped| swapped == index) {
+            return Some(self.msrs[i]);
+        }
+        let field = match index {
+            msr::IA32_EFER => vmcs::GUEST_IA32_EFER,
+            msr::IA32_FS_BASE => vmcs::GUEST_FS_BASE,
+            msr::IA32_GS_BASE => vmcs::GUEST_GS_BASE,
+            vmx_msr::IA32_SYSENTER_CS => vmcs::GUEST_IA32_SYSENTER_CS,
+            vmx_msr::IA32_SYSENTER_ESP => vmcs::GUEST_IA32_SYSENTER_ESP,
+            vmx_msr::IA32_SYSENTER_EIP => vmcs::GUEST_IA32_SYSENTER_EIP,
+            _ => return None,
+        };
+        Some(vmread(field))
+    }
+
+    unsafe fn emulate_rdmsr(&mut self, instruction_len: u64) {
+        match self.guest_msr(self.state.guest.rcx as u32) {
+            Some(value) => {
+                self.state.guest.rax = value & 0xFFFF_FFFF;
+                self.state.guest.rdx = value >> 32;
+                self.advance(instruction_len);
+            }
+            None => self.raise_exception(EXCEPTION_GP, Some(0)),
+        }
+    }
+
+    unsafe fn emulate_wrmsr(&mut self, instruction_len: u64) {
+        let index = self.state.guest.rcx as u32;
+        let value = (self.state.guest.rdx << 32) | (self.state.guest.rax & 0xFFFF_FFFF);
+        let written = match index {
+            msr::IA32_EFER => {
+                // The guest stays in long mode
+                let valid = value & !EFER_GUEST_VALID == 0 && value & efer::LME != 0;
+                if valid {
+                    vmwrite(vmcs::GUEST_IA32_EFER, value | efer::LMA);
+                }
+                valid
+            }
+            msr::IA32_FS_BASE | msr::IA32_GS_BASE
+            | vmx_msr::IA32_SYSENTER_ESP | vmx_msr::IA32_SYSENTER_EIP => {
+                let field = match index {
+                    msr::IA32_FS_BASE => vmcs::GUEST_FS_BASE,
+                    msr::IA32_GS_BASE => vmcs::GUEST_GS_BASE,
+                    vmx_msr::IA32_SYSENTER_ESP => vmcs::GUEST_IA32_SYSENTER_ESP,
+                    _ => vmcs::GUEST_IA32_SYSENTER_EIP,
+                };
+                let valid = is_canonical(value);
+                if valid {
+                    vmwrite(field, value);
+                }
+                valid
+            }
+            vmx_msr::IA32_SYSENTER_CS => {
+                vmwrite(vmcs::GUEST_IA32_SYSENTER_CS, value & 0xFFFF_FFFF);
+                true
+            }
+            _ => match SWAPPED_MSRS.iter().position(|&swapped| swapped == index) {
+                // The host loads these, so they must not fault there
+                Some(i) => {
+                    let valid = match index {
+                        msr::IA32_STAR => true,
+                        msr::IA32_FMASK => value >> 32 == 0,
+                        _ => is_canonical(value),
+                    };
+                    if valid {
+                        self.msrs[i] = value;
+                    }
+                    valid
+                }
+                None => false,
+            },
+        };
+
+        if written {
+            self.advance(instruction_len);
+        } else {
+            self.raise_exception(EXCEPTION_GP, Some(0));
+        }
+    }
+
+    /// Copy guest-physical memory within one page into `buf`
+    fn read_guest_phys(gpas: &GuestPhysicalAddressSpace, addr: u64, buf: &mut [u8]) -> bool {
+        let Ok((paddr, _)) = gpas.fault(addr & !(PAGE_SIZE - 1), false) else {
+            return false;
+        };
+        let src = pmm::paddr_to_vaddr(paddr) + (addr & (PAGE_SIZE - 1)) as usize;
+        unsafe { core::ptr::copy_nonoverlapping(src as *const u8, buf.as_mut_ptr(), buf.len()) };
+        true
+    }
+
+    /// Translate a guest-virtual address with the guest's page tables
+    fn translate(&self, gpas: &GuestPhysicalAddressSpace, addr: u64) -> Option<u64> {
+        let mut table = self.cr3 & PTE_ADDR_MASK;
+        for level in (0..4).rev() {
+            let index = (addr >> (12 + 9 * level)) & 0x1FF;
+            let mut entry = [0u8; 8];
+            if !Self::read_guest_phys(gpas, table + index * 8, &mut entry) {
+                return None;
+            }
+            let entry = u64::from_le_bytes(entry);
+            if entry & PTE_PRESENT == 0 {
+                return None;
+            }
+            if level == 0 || (level < 3 && entry & PTE_LARGE != 0) {
+                let offset_mask = (1u64 << (12 + 9 * level)) - 1;
+                return Some(entry & PTE_ADDR_MASK & !offset_mask | addr & offset_mask);
+            }
+            table = entry & PTE_ADDR_MASK;
+        }
+        None
+    }
+
+    /// Decode the guest memory access that caused the last exit
+    ///
+    /// On success the instruction can be skipped with
+    /// [`Self::skip_instruction`]. Returns a zero-sized access if the
+    /// instruction can't be read or isn't a `MOV` that accesses memory as
+    /// the exit did.
+    pub fn decode_access(&mut self, gpas: &GuestPhysicalAddressSpace, write: bool) -> MemAccess {
+        if !self.long_mode {
+            return MemAccess::default();
+        }
+
+        let mut bytes = [0u8; MAX_INSTRUCTION_LEN];
+        let mut fetched = 0;
+        while fetched < bytes.len() {
+            let addr = self.rip.wrapping_add(fetched as u64);
+            let chunk = (bytes.len() - fetched).min((PAGE_SIZE - (addr & (PAGE_SIZE - 1))) as usize);
+            let Some(gpa) = self.translate(gpas, addr) else {
+                break;
+            };
+            if !Self::read_guest_phys(gpas, gpa, &mut bytes[fetched..fetched + chunk]) {
+                break;
+            }
+            fetched += chunk;
+        }
+
+        let Some(mov) = decode_mov(&bytes[..fetched]) else {
+            return MemAccess::default();
+        };
+        if mov.write != write {
+            return MemAccess::default();
+        }
+        self.skip_len = mov.len as u64;
+        let data = if write {
+            mov.imm.unwrap_or_else(|| self.reg(mov.reg)) & size_mask(mov.size)
+        } else {
+            0
+        };
+        MemAccess { size: mov.size, reg: mov.reg, data }
+    }
+
+    /// Read register state of `kind`
+    pub fn read_state(&self, kind: u32) -> Result<VcpuState> {
+        if kind != vcpu_state::REGS {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let mut state = VcpuState::default();
+        for index in 0..16u8 {
+            state.regs[index as usize] = self.reg(index);
+        }
+        state.regs[vcpu_state::X86_RIP] = self.rip;
+        state.regs[vcpu_state::X86_RFLAGS] = self.rflags;
+        state.regs[vcpu_state::X86_CR3] = self.cr3;
+        Ok(state)
+    }
+
+    /// Write register state of `kind`
+    ///
+    /// Fails with `RX_ERR_INVALID_ARGS` for a non-canonical RIP or a CR3
+    /// beyond the physical address width. Reserved RFLAGS bits are
+    /// dropped.
+    pub fn write_state(&mut self, kind: u32, state: &VcpuState) -> Result {
+        if kind != vcpu_state::REGS {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let rip = state.regs[vcpu_state::X86_RIP];
+        let cr3 = state.regs[vcpu_state::X86_CR3];
+        let address_bits = cpuid(0x8000_0008, 0).eax & 0xFF;
+        if !is_canonical(rip) || cr3 >> address_bits != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        for index in 0..16u8 {
+            self.set_reg(index, state.regs[index as usize]);
+        }
+        self.rip = rip;
+        self.rflags = sanitize_rflags(state.regs[vcpu_state::X86_RFLAGS]);
+        self.cr3 = cr3;
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjust_controls() {
+        // Bit 1 required, bits 0-3 allowed
+        let cap = (0xFu64 << 32) | 0x2;
+        assert_eq!(adjust_controls(cap, 0x1), Ok(0x3));
+        assert_eq!(adjust_controls(cap, 0x10), Err(RX_ERR_NOT_SUPPORTED));
+        assert!(allowed(cap, 0xC));
+        assert!(!allowed(cap, 0x11));
+        assert!(fixed_bits_valid(0b0110, 0b0010, 0b0111));
+        assert!(!fixed_bits_valid(0b0100, 0b0010, 0b0111));
+        assert!(!fixed_bits_valid(0b1010, 0b0010, 0b0111));
+    }
+
+    #[test]
+    fn test_decode_mov() {
+        // mov [rax], ecx
+        assert_eq!(
+            decode_mov(&[0x89, 0x08]),
+            Some(Mov { len: 2, size: 4, write: true, reg: 1, imm: None })
+        );
+        // mov r9, [rbx + 0x10]
+        assert_eq!(
+            decode_mov(&[0x4C, 0x8B, 0x4B, 0x10]),
+            Some(Mov { len: 4, size: 8, write: false, reg: 9, imm: None })
+        );
+        // mov word ptr [rip + 0x1000], 0xBEEF
+        assert_eq!(
+            decode_mov(&[0x66, 0xC7, 0x05, 0x00, 0x10, 0x00, 0x00, 0xEF, 0xBE]),
+            Some(Mov { len: 9, size: 2, write: true, reg: 0, imm: Some(0xBEEF) })
+        );
+        // mov qword ptr [rsp + rcx*8], -1
+        assert_eq!(
+            decode_mov(&[0x48, 0xC7, 0x04, 0xCC, 0xFF, 0xFF, 0xFF, 0xFF]),
+            Some(Mov { len: 8, size: 8, write: true, reg: 0, imm: Some(u64::MAX) })
+        );
+        // mov sil, [rdi] needs REX to name SIL rather than DH
+        assert_eq!(
+            decode_mov(&[0x40, 0x8A, 0x37]),
+            Some(Mov { len: 3, size: 1, write: false, reg: 6, imm: None })
+        );
+        assert_eq!(decode_mov(&[0x8A, 0x37]), None);
+
+        // Register to register, other opcodes, and truncated instructions
+        assert_eq!(decode_mov(&[0x89, 0xC8]), None);
+        assert_eq!(decode_mov(&[0x0F, 0xB6, 0x00]), None);
+        assert_eq!(decode_mov(&[0x8B, 0x80, 0x00, 0x10]), None);
+    }
+
+    #[test]
+    fn test_io_exit() {
+        // out 0x3F8, al
+        let qualification = (0x3F8 << 16) | (1 << 6);
+        assert_eq!(
+            io_exit(qualification, 0x1234_5678),
+            Some(GuestExit::Io { port: 0x3F8, access_size: 1, input: false, data: 0x78 })
+        );
+        // in eax, dx
+        let qualification = (0xCFC << 16) | (1 << 3) | 3;
+        assert_eq!(
+            io_exit(qualification, 0),
+            Some(GuestExit::Io { port: 0xCFC, access_size: 4, input: true, data: 0 })
+        );
+        // rep outsb
+        assert_eq!(io_exit((0x80 << 16) | (1 << 4) | (1 << 5), 0), None);
+    }
+
+    #[test]
+    fn test_guest_register_checks() {
+        assert!(is_canonical(0x0000_7FFF_FFFF_F000));
+        assert!(is_canonical(0xFFFF_8000_0000_0000));
+        assert!(!is_canonical(0x0000_8000_0000_0000));
+        assert_eq!(sanitize_rflags(0), RFLAGS_FIXED);
+        assert_eq!(sanitize_rflags(u64::MAX) & rflags::VM, 0);
+        assert_ne!(sanitize_rflags(rflags::IF) & rflags::IF, 0);
+        assert_eq!(size_mask(2), 0xFFFF);
+        assert_eq!(size_mask(8), u64::MAX);
+    }
+}
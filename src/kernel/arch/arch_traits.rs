@@ -13,6 +13,7 @@
 //! allowing architecture-specific optimizations where needed.
 
 
+use crate::kernel::object::exception::DebugRegs;
 use crate::rustux::types::*;
 
 /// Virtual memory size (48-bit address space)
@@ -616,6 +617,137 @@ pub trait ArchDebug {
     unsafe fn disable_hw_breakpoints() {
         // Default: do nothing
     }
+
+    /// Hardware breakpoints a user thread can arm
+    ///
+    /// # Returns
+    ///
+    /// Up to `MAX_HW_BREAKPOINTS`, 0 if not supported
+    fn user_breakpoint_count() -> u32 {
+        0 // Default: not supported
+    }
+
+    /// Read a thread's hardware breakpoints
+    ///
+    /// # Arguments
+    ///
+    /// * `thread` - Thread to read
+    /// * `regs` - Filled in, except `count`
+    fn read_user_breakpoints(thread: &crate::kernel::thread::Thread, regs: &mut DebugRegs) {
+        let _ = (thread, regs); // Default: none armed
+    }
+
+    /// Replace a thread's hardware breakpoints
+    ///
+    /// The thread's registers are loaded the next time it is switched in.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread` - Thread to change, not the current one
+    /// * `regs` - Breakpoints to arm
+    ///
+    /// # Returns
+    ///
+    /// 0 on success, negative if not supported or an address is bad
+    unsafe fn write_user_breakpoints(thread: &crate::kernel::thread::Thread, regs: &DebugRegs) -> i32 {
+        let _ = (thread, regs);
+        -1 // Default: not supported
+    }
+}
+
+/// Performance monitoring unit
+///
+/// Counters are numbered from 0 and every method acts on the current CPU.
+/// The `PERFMON_*` constants are in `lib::perfmon`.
+pub trait ArchPerfmon {
+    /// Programmable counters, 0 if there is no usable PMU
+    fn perfmon_counter_count() -> u32 {
+        0 // Default: not supported
+    }
+
+    /// Width of the counters in bits
+    fn perfmon_counter_bits() -> u32 {
+        0
+    }
+
+    /// Hardware event for a generic `PERFMON_EVENT_*` event
+    ///
+    /// # Returns
+    ///
+    /// The value to pass to `perfmon_program`, `None` if the CPU can't
+    /// count the event
+    fn perfmon_event(event: u32) -> Option<u64> {
+        let _ = event;
+        None
+    }
+
+    /// Prepare this CPU's PMU and its overflow interrupt
+    unsafe fn perfmon_init() {
+        // Default: nothing to do
+    }
+
+    /// Program a stopped counter
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Counter to program
+    /// * `event` - Hardware event, from `perfmon_event` or raw
+    /// * `value` - Starting count
+    /// * `flags` - `PERFMON_FLAG_*`: the privilege levels to count and
+    ///   whether to interrupt on overflow
+    unsafe fn perfmon_program(index: u32, event: u64, value: u64, flags: u32) {
+        let _ = (index, event, value, flags);
+    }
+
+    /// Run the counters in `mask` and stop the others
+    unsafe fn perfmon_enable(mask: u32) {
+        let _ = mask;
+    }
+
+    /// Read a counter
+    fn perfmon_read(index: u32) -> u64 {
+        let _ = index;
+        0
+    }
+
+    /// Counters that overflowed, clearing their overflow status
+    unsafe fn perfmon_ack_overflow() -> u32 {
+        0
+    }
+}
+
+/// Hardware instruction trace
+///
+/// Branch trace written by the CPU straight to a physically contiguous
+/// buffer: Intel PT on x86_64, ETE with TRBE on arm64. Every method acts
+/// on the current CPU. The `ITRACE_*` constants are in `lib::itrace`.
+pub trait ArchItrace {
+    /// Whether the CPU can trace into a single buffer
+    fn itrace_supported() -> bool {
+        false // Default: not supported
+    }
+
+    /// Start tracing
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - Physical address of the buffer, aligned to its size
+    /// * `size` - Size of the buffer, a power of two of at least a page
+    /// * `offset` - Where in the buffer to continue writing
+    /// * `flags` - `ITRACE_FLAG_*`: the privilege levels to trace
+    unsafe fn itrace_start(base: PAddr, size: usize, offset: usize, flags: u32) {
+        let _ = (base, size, offset, flags);
+    }
+
+    /// Stop tracing and flush the trace to memory
+    ///
+    /// # Returns
+    ///
+    /// Where in the buffer the trace ends, and whether tracing had
+    /// stopped because the buffer filled
+    unsafe fn itrace_stop() -> (usize, bool) {
+        (0, false)
+    }
 }
 
 /// FPU state management
@@ -648,6 +780,28 @@ pub trait ArchFpu {
 
     /// Disable FPU access
     unsafe fn disable();
+
+    /// Bytes needed to save the state
+    ///
+    /// May be more than `size_of::<FpuState>()` when the state depends on
+    /// the CPU, e.g. XSAVE components or the SVE and V vector lengths.
+    fn state_size() -> usize {
+        core::mem::size_of::<Self::FpuState>()
+    }
+
+    /// Alignment the save area needs
+    fn state_align() -> usize {
+        core::mem::align_of::<Self::FpuState>()
+    }
+
+    /// Fill a zeroed save area with the state a new thread starts with
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - Zeroed area of [`state_size`](Self::state_size) bytes
+    unsafe fn init_state(_state: *mut Self::FpuState) {
+        // Default: all zeros
+    }
 }
 
 /// Generic architecture trait combining all traits
@@ -667,6 +821,8 @@ pub trait Arch:
     + ArchUserAccess
     + ArchUserEntry
     + ArchDebug
+    + ArchPerfmon
+    + ArchItrace
     + ArchFpu
 {
 }
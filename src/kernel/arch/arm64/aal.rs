@@ -13,6 +13,7 @@
 use crate::kernel::arch::arch_traits::*;
 use crate::arch::arm64;
 use crate::arch::arm64::registers;
+use crate::kernel::object::exception::{DebugRegs, MAX_HW_BREAKPOINTS};
 use crate::rustux::types::*;
 
 /// Marker type for ARM64 architecture
@@ -452,6 +453,13 @@ impl ArchUserEntry for Arm64Arch {
 
 // ============= ArchDebug Implementation =============
 
+/// DBGBCR: breakpoint enabled
+const DBGBCR_E: u32 = 1 << 0;
+
+/// DBGBCR: enabled, EL0 only (PMC 0b10), all four bytes of an A64
+/// instruction (BAS 0b1111)
+const DBGBCR_USER_INSTRUCTION: u32 = DBGBCR_E | 0b10 << 1 | 0b1111 << 5;
+
 impl ArchDebug for Arm64Arch {
     fn read_perf_counter() -> u64 {
         unsafe {
@@ -584,6 +592,95 @@ impl ArchDebug for Arm64Arch {
         mdscr &= !(1 << 12);  // Disable breakpoints
         core::arch::asm!("msr mdscr_el1, {}", in(reg) mdscr, options(nostack));
     }
+
+    fn user_breakpoint_count() -> u32 {
+        arm64::arm64_hw_breakpoint_count().min(MAX_HW_BREAKPOINTS as u32)
+    }
+
+    fn read_user_breakpoints(thread: &crate::kernel::thread::Thread, regs: &mut DebugRegs) {
+        for (slot, bp) in thread.arch.debug_state.hw_bps[..MAX_HW_BREAKPOINTS].iter().enumerate() {
+            regs.hw_bps[slot] = bp.dbgbvr;
+            if bp.dbgbcr & DBGBCR_E != 0 {
+                regs.enabled |= 1 << slot;
+            }
+        }
+    }
+
+    unsafe fn write_user_breakpoints(thread: &crate::kernel::thread::Thread, regs: &DebugRegs) -> i32 {
+        if regs.enabled >> Self::user_breakpoint_count() != 0 {
+            return -1;
+        }
+        let mut state = arm64::thread::Arm64DebugState::default();
+        for slot in 0..MAX_HW_BREAKPOINTS {
+            if regs.enabled & (1 << slot) == 0 {
+                continue;
+            }
+            let addr = regs.hw_bps[slot];
+            if addr % 4 != 0 || !Self::is_user_address(addr as VAddr) {
+                return -1;
+            }
+            state.hw_bps[slot].dbgbvr = addr;
+            state.hw_bps[slot].dbgbcr = DBGBCR_USER_INSTRUCTION;
+        }
+
+        // Loaded into the CPU when the thread is next switched in
+        let arch = &thread.arch as *const crate::kernel::thread::ArchData as *mut crate::kernel::thread::ArchData;
+        (*arch).debug_state = state;
+        (*arch).track_debug_state = true;
+        0
+    }
+}
+
+// ============= ArchPerfmon Implementation =============
+
+impl ArchPerfmon for Arm64Arch {
+    fn perfmon_counter_count() -> u32 {
+        arm64::perfmon::arm64_perfmon_counter_count()
+    }
+
+    fn perfmon_counter_bits() -> u32 {
+        arm64::perfmon::arm64_perfmon_counter_bits()
+    }
+
+    fn perfmon_event(event: u32) -> Option<u64> {
+        arm64::perfmon::arm64_perfmon_event(event)
+    }
+
+    unsafe fn perfmon_init() {
+        arm64::perfmon::arm64_perfmon_init();
+    }
+
+    unsafe fn perfmon_program(index: u32, event: u64, value: u64, flags: u32) {
+        arm64::perfmon::arm64_perfmon_program(index, event, value, flags);
+    }
+
+    unsafe fn perfmon_enable(mask: u32) {
+        arm64::perfmon::arm64_perfmon_enable(mask);
+    }
+
+    fn perfmon_read(index: u32) -> u64 {
+        arm64::perfmon::arm64_perfmon_read(index)
+    }
+
+    unsafe fn perfmon_ack_overflow() -> u32 {
+        arm64::perfmon::arm64_perfmon_ack_overflow()
+    }
+}
+
+// ============= ArchItrace Implementation =============
+
+impl ArchItrace for Arm64Arch {
+    fn itrace_supported() -> bool {
+        arm64::itrace::arm64_itrace_supported()
+    }
+
+    unsafe fn itrace_start(base: PAddr, size: usize, offset: usize, flags: u32) {
+        arm64::itrace::arm64_itrace_start(base, size, offset, flags);
+    }
+
+    unsafe fn itrace_stop() -> (usize, bool) {
+        arm64::itrace::arm64_itrace_stop()
+    }
 }
 
 // ============= ArchFpu Implementation =============
@@ -614,6 +711,10 @@ impl ArchFpu for Arm64Arch {
     unsafe fn disable() {
         arm64::fpu::arm64_fpu_disable();
     }
+
+    fn state_size() -> usize {
+        arm64::fpu::arm64_fpu_state_size()
+    }
 }
 
 // ============= Arch Marker Trait Implementation =============
@@ -48,6 +48,7 @@ const PMCR_EL0_LONG_COUNTER_BIT: u64 = 1 << 6;
 const PMUSERENR_EL0_ENABLE: u64 = 1 << 0;  // Enable EL0 access to cycle counter.
 
 // System Control Register, EL1.
+const SCTLR_EL1_SPAN: u64 = 1 << 23; // Leave PSTATE.PAN unchanged on exception to EL1.
 const SCTLR_EL1_UCI: u64 = 1 << 26; // Allow certain cache ops in EL0.
 const SCTLR_EL1_UCT: u64 = 1 << 15; // Allow EL0 access to CTR register.
 const SCTLR_EL1_DZE: u64 = 1 << 14; // Allow EL0 to use DC ZVA.
@@ -252,6 +253,27 @@ fn arm64_cpu_early_init() {
     // Save all of the features of the cpu.
     feature::arm64_feature_init();
 
+    // Size SVE state and make the first fpu use trap.
+    arm64::arm64_fpu_init();
+
+    // Deny EL1 data accesses to EL0 pages outside of the user copy routines.
+    // Clearing SPAN makes the CPU set PSTATE.PAN on every exception taken to
+    // EL1, so user code can't arrive in the kernel with PAN cleared.
+    if feature::arm64_feature_test(arm64::ARM64_FEATURE_PAN) {
+        unsafe {
+            core::arch::asm!(
+                "mrs {0}, sctlr_el1",
+                "bic {0}, {0}, {1}",
+                "msr sctlr_el1, {0}",
+                "isb sy",
+                out(reg) _,
+                in(reg) SCTLR_EL1_SPAN,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+        mmu::arm64_user_access_end();
+    }
+
     // Enable cycle counter.
     unsafe {
         core::arch::asm!(
@@ -358,7 +380,7 @@ pub fn arch_init() {
 #[no_mangle]
 pub extern "C" fn arch_idle_thread_routine(_arg: *mut core::ffi::c_void) -> i32 {
     loop {
-        unsafe { core::arch::asm!("wfi", options(nomem, nostack)) };
+        crate::kernel::power::idle::cpu_idle();
     }
 }
 
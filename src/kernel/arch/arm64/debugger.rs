@@ -141,36 +141,37 @@ pub fn arch_set_fp_regs(_thread: &mut Thread, _input: &rx_thread_state_fp_regs_t
 }
 
 pub fn arch_get_vector_regs(thread: &Thread, out: &mut rx_thread_state_vector_regs_t) -> rx_status_t {
-    let thread_lock_guard = Guard::<_, IrqSave>::new(ThreadLock::get());
-
-    // ARM64 FP state access not yet implemented
-    // fpstate is a raw pointer (*mut c_void) - need proper FP state structure
-    if thread.arch.fpstate.is_null() {
-        // Initialize with zeros
-        out.fpcr = 0;
-        out.fpsr = 0;
+    // The save area is synced with the live registers if |thread| is the
+    // current thread, and holds the reset state if it never used the fpu.
+    let done = thread::fpu::with_state(thread, false, |state| unsafe {
+        out.fpcr = (*state).fpcr;
+        out.fpsr = (*state).fpsr;
         for i in 0..32 {
-            out.v[i].low = 0;
-            out.v[i].high = 0;
+            let (low, high) = arm64::fpu::arm64_fpu_read_v(state, i);
+            out.v[i].low = low;
+            out.v[i].high = high;
         }
-    } else {
-        // TODO: Implement proper FP state access
-        return RX_ERR_NOT_SUPPORTED;
-    }
+    });
 
-    RX_OK
+    match done {
+        Some(()) => RX_OK,
+        None => RX_ERR_NO_MEMORY,
+    }
 }
 
 pub fn arch_set_vector_regs(thread: &mut Thread, input: &rx_thread_state_vector_regs_t) -> rx_status_t {
-    let thread_lock_guard = Guard::<_, IrqSave>::new(ThreadLock::get());
+    let done = thread::fpu::with_state(thread, true, |state| unsafe {
+        (*state).fpcr = input.fpcr;
+        (*state).fpsr = input.fpsr;
+        for i in 0..32 {
+            arm64::fpu::arm64_fpu_write_v(state, i, input.v[i].low, input.v[i].high);
+        }
+    });
 
-    // ARM64 FP state access not yet implemented
-    if thread.arch.fpstate.is_null() {
-        // TODO: Implement proper FP state setting
-        return RX_ERR_NOT_SUPPORTED;
+    match done {
+        Some(()) => RX_OK,
+        None => RX_ERR_NO_MEMORY,
     }
-
-    RX_OK
 }
 
 pub fn arch_get_debug_regs(thread: &Thread, out: &mut rx_thread_state_debug_regs_t) -> rx_status_t {
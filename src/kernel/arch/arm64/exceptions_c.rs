@@ -23,6 +23,7 @@ use crate::rustux::types::err::*;
 use crate::sys::rx_excp_type_t;
 use core::fmt::Write;
 
+use crate::kernel::object::exception as object_exception;
 use crate::kernel::thread;
 
 use crate::platform;
@@ -32,6 +33,10 @@ use crate::vm;
 
 use crate::lib::counters;
 use crate::lib::crashlog;
+use crate::lib::gdbstub;
+use crate::lib::ktrace;
+use crate::lib::perfmon;
+use crate::lib::profiler;
 
 use crate::rustux::syscalls::exception::*;
 use crate::rustux::types::*;
@@ -58,7 +63,7 @@ KCOUNTER!(
 KCOUNTER!(
     EXCEPTIONS_UNKNOWN, "kernel.exceptions.unknown");
 
-fn dump_iframe(iframe: &arm64::arm64_iframe_long) {
+pub(crate) fn dump_iframe(iframe: &arm64::arm64_iframe_long) {
     println!("iframe {:p}:", iframe);
     println!("x0  {:#18x} x1  {:#18x} x2  {:#18x} x3  {:#18x}", iframe.r[0], iframe.r[1], iframe.r[2], iframe.r[3]);
     println!("x4  {:#18x} x5  {:#18x} x6  {:#18x} x7  {:#18x}", iframe.r[4], iframe.r[5], iframe.r[6], iframe.r[7]);
@@ -112,21 +117,48 @@ fn try_dispatch_user_exception(
     try_dispatch_user_data_fault_exception(type_, iframe, esr, 0)
 }
 
-fn exception_die(iframe: &mut arm64::arm64_iframe_long, esr: u32) -> ! {
-    platform::platform_panic_start();
+/// Hand a breakpoint, watchpoint or single step taken at EL0 to the
+/// thread's exception channel handler
+///
+/// The kind is decoded from `esr`, which the handler gets as well. Falls
+/// back to the legacy dispatch if no job has a handler.
+fn try_dispatch_debug_exception(
+    type_: rx_excp_type_t,
+    iframe: &mut arm64::arm64_iframe_long,
+    esr: u32,
+    far: u64
+) {
+    if let Some(event) = object_exception::DebugEvent::from_esr(esr as u64) {
+        let mut regs = object_exception::GeneralRegs {
+            pc: iframe.elr,
+            sp: iframe.usp,
+            fp: iframe.r[29],
+            flags: iframe.spsr,
+            regs: [0; 32],
+        };
+        regs.regs[..30].copy_from_slice(&iframe.r);
+        regs.regs[30] = iframe.lr;
+        regs.regs[31] = iframe.usp;
+
+        // The thread blocks until its handler is done with it
+        arch_ops::arch_enable_ints();
+        let handled = object_exception::dispatch_debug(event, &regs, far, esr as u64);
+        arch_ops::arch_disable_ints();
+        if handled {
+            return;
+        }
+    }
+    let _ = try_dispatch_user_data_fault_exception(type_, iframe, esr, far);
+}
 
+fn exception_die(iframe: &mut arm64::arm64_iframe_long, esr: u32) -> ! {
     let ec = bits::BITS_SHIFT(esr, 31, 26);
     let il = bits::BIT(esr, 25);
     let iss = bits::BITS(esr, 24, 0);
 
-    /* fatal exception, die here */
-    println!("ESR 0x{:x}: ec 0x{:x}, il 0x{:x}, iss 0x{:x}", esr, ec, il, iss);
-    dump_iframe(iframe);
-    crashlog::crashlog.iframe = (iframe as *mut arm64::arm64_iframe_long) as *mut u8;
-
-    platform::platform_halt(platform::HALT_ACTION_HALT, platform::HALT_REASON_SW_PANIC);
-    // This never returns
-    loop {}
+    /* fatal exception, die here; the panic path dumps the iframe */
+    crashlog::set_iframe((iframe as *mut arm64::arm64_iframe_long) as *mut u8);
+    panic!("fatal exception: ESR 0x{:x}: ec 0x{:x}, il 0x{:x}, iss 0x{:x}", esr, ec, il, iss);
 }
 
 fn arm64_unknown_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32, esr: u32) {
@@ -141,16 +173,22 @@ fn arm64_unknown_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags:
 
 fn arm64_brk_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32, esr: u32) {
     if unlikely((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) == 0) {
-        /* trapped inside the kernel, this is bad */
+        /* trapped inside the kernel, this is bad unless the debugger is attached */
+        if gdbstub::handle_exception(iframe, gdbstub::SIGTRAP) {
+            return;
+        }
         println!("BRK in kernel: PC at {:#x}", iframe.elr);
         exception_die(iframe, esr);
     }
-    let _ = try_dispatch_user_exception(RX_EXCP_SW_BREAKPOINT, iframe, esr);
+    try_dispatch_debug_exception(RX_EXCP_SW_BREAKPOINT, iframe, esr, 0);
 }
 
 fn arm64_hw_breakpoint_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32, esr: u32) {
     if unlikely((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) == 0) {
-        /* trapped inside the kernel, this is bad */
+        /* trapped inside the kernel, this is bad unless the debugger is attached */
+        if gdbstub::handle_exception(iframe, gdbstub::SIGTRAP) {
+            return;
+        }
         println!("HW breakpoint in kernel: PC at {:#x}", iframe.elr);
         exception_die(iframe, esr);
     }
@@ -158,20 +196,41 @@ fn arm64_hw_breakpoint_handler(iframe: &mut arm64::arm64_iframe_long, exception_
     // We don't need to save the debug state because it doesn't change by an exception. The only
     // way to change the debug state is through the thread write syscall.
 
-    // NOTE: ARM64 Doesn't provide a good way to communicate exception status (without exposing ESR
-    //       to userspace). This means a debugger will have to compare the registers with the PC
-    //       on the exceptions to find out which breakpoint triggered the exception.
-    let _ = try_dispatch_user_exception(RX_EXCP_HW_BREAKPOINT, iframe, esr);
+    // ARM64 doesn't say which breakpoint fired. The handler gets the ESR and compares the PC
+    // with the thread's breakpoints to find out.
+    try_dispatch_debug_exception(RX_EXCP_HW_BREAKPOINT, iframe, esr, 0);
+}
+
+fn arm64_watchpoint_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32, esr: u32) {
+    if unlikely((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) == 0) {
+        /* trapped inside the kernel, this is bad unless the debugger is attached */
+        if gdbstub::handle_exception(iframe, gdbstub::SIGTRAP) {
+            return;
+        }
+        println!("watchpoint in kernel: PC at {:#x}", iframe.elr);
+        exception_die(iframe, esr);
+    }
+
+    // FAR holds the data address that was accessed
+    let far = unsafe {
+        let far: u64;
+        core::arch::asm!("mrs {}, far_el1", out(reg) far);
+        far
+    };
+    try_dispatch_debug_exception(RX_EXCP_HW_BREAKPOINT, iframe, esr, far);
 }
 
 fn arm64_step_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32, esr: u32) {
     if unlikely((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) == 0) {
-        /* trapped inside the kernel, this is bad */
+        /* trapped inside the kernel, this is bad unless the debugger is attached */
+        if gdbstub::handle_exception(iframe, gdbstub::SIGTRAP) {
+            return;
+        }
         println!("software step in kernel: PC at {:#x}", iframe.elr);
         exception_die(iframe, esr);
     }
-    // TODO(RX-3037): Is it worth separating this into two separate exceptions?
-    let _ = try_dispatch_user_exception(RX_EXCP_HW_BREAKPOINT, iframe, esr);
+    // Reported as a hardware breakpoint with the single step subtype
+    try_dispatch_debug_exception(RX_EXCP_HW_BREAKPOINT, iframe, esr, 0);
 }
 
 fn arm64_fpu_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32, esr: u32) {
@@ -180,8 +239,13 @@ fn arm64_fpu_handler(iframe: &mut arm64::arm64_iframe_long, exception_flags: u32
         println!("invalid fpu use in kernel: PC at {:#x}", iframe.elr);
         exception_die(iframe, esr);
     }
-    unsafe {
-        arm64::arm64_fpu_exception(iframe, exception_flags);
+    if arm64::arm64_fpu_exception(iframe, exception_flags) {
+        return;
+    }
+    /* no fpu state for the thread, let the user exception handler have it */
+    if try_dispatch_user_exception(RX_EXCP_GENERAL, iframe, esr) != RX_OK {
+        println!("could not give thread the fpu");
+        exception_die(iframe, esr);
     }
 }
 
@@ -209,7 +273,8 @@ fn arm64_instruction_abort_handler(iframe: &mut arm64::arm64_iframe_long, except
             iframe.elr, is_user, far, esr, iss);
 
     arch_ops::arch_enable_ints();
-    EXCEPTIONS_PAGE.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    EXCEPTIONS_PAGE.add(1);
+    ktrace::write(ktrace::TAG_PAGE_FAULT, 0, far as u64, pf_flags as u64);
     CPU_STATS_INC!(page_faults);
 
     let err = fault::vmm_page_fault_handler(far as usize, pf_flags);
@@ -223,7 +288,7 @@ fn arm64_instruction_abort_handler(iframe: &mut arm64::arm64_iframe_long, except
     // If this is from user space, let the user exception handler
     // get a shot at it.
     if is_user {
-        EXCEPTIONS_USER.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        EXCEPTIONS_USER.add(1);
         if try_dispatch_user_data_fault_exception(RX_EXCP_FATAL_PAGE_FAULT, iframe, esr, far) == RX_OK {
             return;
         }
@@ -264,7 +329,8 @@ fn arm64_data_abort_handler(iframe: &mut arm64::arm64_iframe_long, exception_fla
 
     if likely(dfsc != DFSC_ALIGNMENT_FAULT as u64) {
         arch_ops::arch_enable_ints();
-        EXCEPTIONS_PAGE.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        EXCEPTIONS_PAGE.add(1);
+        ktrace::write(ktrace::TAG_PAGE_FAULT, 0, far as u64, pf_flags as u64);
 
         let err = fault::vmm_page_fault_handler(far as usize, pf_flags);
 
@@ -288,7 +354,7 @@ fn arm64_data_abort_handler(iframe: &mut arm64::arm64_iframe_long, exception_fla
     // If this is from user space, let the user exception handler
     // get a shot at it.
     if is_user {
-        EXCEPTIONS_USER.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        EXCEPTIONS_USER.add(1);
         let excp_type = if unlikely(dfsc == DFSC_ALIGNMENT_FAULT as u64) {
             RX_EXCP_UNALIGNED_ACCESS
         } else {
@@ -336,15 +402,15 @@ pub extern "C" fn arm64_sync_exception(
 
     match ec {
         0b000000 => { /* unknown reason */
-            EXCEPTIONS_UNKNOWN.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            EXCEPTIONS_UNKNOWN.add(1);
             arm64_unknown_handler(iframe, exception_flags, esr);
         },
         0b111000 | 0b111100 => { /* BRK from arm32 or arm64 */
-            EXCEPTIONS_BRKPT.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            EXCEPTIONS_BRKPT.add(1);
             arm64_brk_handler(iframe, exception_flags, esr);
         },
-        0b000111 => { /* floating point */
-            EXCEPTIONS_FPU.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        0b000111 | 0b011001 => { /* floating point or sve */
+            EXCEPTIONS_FPU.add(1);
             arm64_fpu_handler(iframe, exception_flags, esr);
         },
         0b010001 | 0b010101 => { /* syscall from arm32 or arm64 */
@@ -363,6 +429,9 @@ pub extern "C" fn arm64_sync_exception(
         0b110010 | 0b110011 => { /* software step from lower level or same level */
             arm64_step_handler(iframe, exception_flags, esr);
         },
+        0b110100 | 0b110101 => { /* watchpoint from lower level or same level */
+            arm64_watchpoint_handler(iframe, exception_flags, esr);
+        },
         _ => {
             /* TODO: properly decode more of these */
             if unlikely((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) == 0) {
@@ -371,7 +440,7 @@ pub extern "C" fn arm64_sync_exception(
                 exception_die(iframe, esr);
             }
             /* let the user exception handler get a shot at it */
-            EXCEPTIONS_UNHANDLED.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            EXCEPTIONS_UNHANDLED.add(1);
             if try_dispatch_user_exception(RX_EXCP_GENERAL, iframe, esr) == RX_OK {
                 // Handled successfully
             } else {
@@ -408,16 +477,28 @@ pub extern "C" fn arm64_irq(iframe: *mut arm64::arm64_iframe_short, exception_fl
 
     trace::LTRACEF!("iframe {:p}, flags 0x{:x}", iframe, exception_flags);
 
-    interrupt::int_handler_start();
+    let state = interrupt::int_handler_start();
 
-    EXCEPTIONS_IRQ.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    EXCEPTIONS_IRQ.add(1);
+    ktrace::write(ktrace::TAG_IRQ_ENTER, 0, 0, 0);
+
+    // The timer isn't told apart from other IRQs here; the profiler keeps
+    // to its own period, and counter overflows are checked for on every
+    // IRQ. The short frame doesn't save x29, so kernel samples have no
+    // backtrace.
+    unsafe {
+        let frame = &*iframe;
+        let lower_el = (exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) != 0;
+        perfmon::overflow(frame.elr, 0, lower_el);
+        profiler::sample(frame.elr, 0, lower_el);
+    }
     unsafe {
         platform::platform_irq();
     }
+    ktrace::write(ktrace::TAG_IRQ_EXIT, 0, 0, 0);
 
-    // The int_handler_finish needs to be called with saved state
-    // For now, just assume no preemption until proper state saving is implemented
-    let do_preempt = false;
+    // Only the outermost handler preempts, and only if asked to
+    let do_preempt = interrupt::int_handler_finish(state);
 
     /* if we came from user space, check to see if we have any signals to handle */
     if unlikely((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) != 0) {
@@ -477,12 +558,8 @@ pub extern "C" fn arm64_invalid_exception(iframe: *mut arm64::arm64_iframe_long,
     // restore the percpu pointer (x18) unconditionally
     unsafe { arm64_restore_percpu_pointer(); }
 
-    println!("invalid exception, which 0x{:x}", which);
-    unsafe {
-        dump_iframe(&*iframe);
-    }
-
-    platform::platform_halt(platform::HALT_ACTION_HALT, platform::HALT_REASON_SW_PANIC);
+    crashlog::set_iframe(iframe as *mut u8);
+    panic!("invalid exception, which 0x{:x}", which);
 }
 
 /* called from assembly */
@@ -534,7 +611,7 @@ pub fn arch_dump_exception_context(context: &exception::arch_exception_context_t
         let mut buf = [0u8; 256];
         if unsafe { user_copy::arch_copy_from_user(buf.as_mut_ptr(), iframe.usp as *const u8, buf.len()) } == RX_OK {
             println!("bottom of user stack at 0x{:x}:", iframe.usp);
-            debug::hexdump_ex(buf.as_ptr(), buf.len(), iframe.usp as usize);
+            debug::hexdump(&buf, iframe.usp as u64);
         }
     }
 }
@@ -325,6 +325,9 @@ pub fn arm64_feature_init() {
             if bits::BITS_SHIFT(isar0, 47, 44) >= 1 {
                 arm64_features |= arm64::RX_ARM64_FEATURE_ISA_DP;
             }
+            if bits::BITS_SHIFT(isar0, 63, 60) >= 1 {
+                arm64_features |= arm64::ARM64_FEATURE_ISA_RNDR;
+            }
 
             let isar1: u64;
             core::arch::asm!("mrs {}, id_aa64isar1_el1", out(reg) isar1);
@@ -342,6 +345,13 @@ pub fn arm64_feature_init() {
             if bits::BITS_SHIFT(pfr0, 23, 20) < 0b1111 {
                 arm64_features |= arm64::RX_ARM64_FEATURE_ISA_ASIMD;
             }
+
+            let mmfr1: u64;
+            core::arch::asm!("mrs {}, id_aa64mmfr1_el1", out(reg) mmfr1);
+
+            if mmfr1 & arm64::ARM64_MMFR1_PAN_MASK != 0 {
+                arm64_features |= arm64::ARM64_FEATURE_PAN;
+            }
         }
 
         // read the cache info for each cpu
@@ -377,6 +387,8 @@ fn print_feature() {
         ("sm4", arm64::RX_ARM64_FEATURE_ISA_SM4),
         ("dp", arm64::RX_ARM64_FEATURE_ISA_DP),
         ("dpb", arm64::RX_ARM64_FEATURE_ISA_DPB),
+        ("rndr", arm64::ARM64_FEATURE_ISA_RNDR),
+        ("pan", arm64::ARM64_FEATURE_PAN),
     ];
 
     print!("ARM Features: ");
@@ -5,14 +5,24 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT
 
+//! ARM64 FPSIMD and SVE state
+//!
+//! Threads run with CPACR_EL1.FPEN (and ZEN, with SVE) cleared, so their
+//! first FP, SIMD or SVE instruction traps to [`arm64_fpu_exception`],
+//! which hands the thread the FPU through
+//! [`thread::fpu`](crate::kernel::thread::fpu).
+//!
+//! Without SVE the save area is just [`fpstate`]. With SVE it is followed
+//! by the Z, P and FFR registers at the vector length set in ZCR_EL1; the
+//! V registers are then the low 128 bits of the Z registers and are not
+//! kept in `fpstate.regs`.
+
 use crate::arch::arm64;
 // Re-export fpstate publicly so it can be re-exported from parent modules
 pub use crate::arch::arm64::include::arch::arch_thread::fpstate;
 use crate::bits;
-use crate::kernel::thread::{self, Thread};
-use crate::trace::*;
-
-const LOCAL_TRACE: bool = false;
+use crate::kernel::thread;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 /* FPEN bits in the cpacr register
  * 0 means all fpu instructions fault
@@ -21,151 +31,242 @@ const LOCAL_TRACE: bool = false;
  */
 const FPU_ENABLE_MASK: u64 = 3 << 20;
 
+/* ZEN bits in the cpacr register, with the same encoding as FPEN */
+const SVE_ENABLE_MASK: u64 = 3 << 16;
+
+/* ZCR_EL1, by encoding so the assembler needn't know about SVE */
+const ZCR_EL1_LEN_MAX: u64 = 0x1ff;
+
+/// SVE vector length in bytes, 0 without SVE
+static SVE_VL: AtomicUsize = AtomicUsize::new(0);
+
+/// Offset of the SVE registers in a save area
+pub const SVE_STATE_OFFSET: usize = (core::mem::size_of::<fpstate>() + 15) & !15;
+
 #[inline]
 fn is_fpu_enabled(cpacr: u32) -> bool {
     bits::BITS(cpacr, 21, 20) != 0
 }
 
-fn arm64_fpu_load_state(t: &Thread) {
-    let fpstate_ptr = t.arch.fpstate;
-    let fpstate = unsafe { &*(fpstate_ptr as *const fpstate) };
+#[inline]
+fn enable_mask() -> u64 {
+    if arm64_sve_vl() != 0 {
+        FPU_ENABLE_MASK | SVE_ENABLE_MASK
+    } else {
+        FPU_ENABLE_MASK
+    }
+}
 
-    LTRACEF!("cpu {}, thread {}, load fpstate {:p}\n", arch_curr_cpu_num(), t.name, fpstate_ptr);
+unsafe fn read_cpacr() -> u64 {
+    let cpacr: u64;
+    core::arch::asm!("mrs {}, cpacr_el1", out(reg) cpacr);
+    cpacr
+}
 
-    // Static assertion to ensure size is correct
-    assert_eq!(core::mem::size_of_val(&fpstate.regs), 16 * 32);
+unsafe fn write_cpacr(cpacr: u64) {
+    core::arch::asm!("msr cpacr_el1, {}", in(reg) cpacr);
+    core::arch::asm!("isb sy");
+}
 
-    unsafe {
-        core::arch::asm!(
-            "ldp     q0, q1, [{0}, #(0 * 32)]",
-            "ldp     q2, q3, [{0}, #(1 * 32)]",
-            "ldp     q4, q5, [{0}, #(2 * 32)]",
-            "ldp     q6, q7, [{0}, #(3 * 32)]",
-            "ldp     q8, q9, [{0}, #(4 * 32)]",
-            "ldp     q10, q11, [{0}, #(5 * 32)]",
-            "ldp     q12, q13, [{0}, #(6 * 32)]",
-            "ldp     q14, q15, [{0}, #(7 * 32)]",
-            "ldp     q16, q17, [{0}, #(8 * 32)]",
-            "ldp     q18, q19, [{0}, #(9 * 32)]",
-            "ldp     q20, q21, [{0}, #(10 * 32)]",
-            "ldp     q22, q23, [{0}, #(11 * 32)]",
-            "ldp     q24, q25, [{0}, #(12 * 32)]",
-            "ldp     q26, q27, [{0}, #(13 * 32)]",
-            "ldp     q28, q29, [{0}, #(14 * 32)]",
-            "ldp     q30, q31, [{0}, #(15 * 32)]",
-            "msr     fpcr, {1}",
-            "msr     fpsr, {2}",
-            in(reg) &fpstate.regs[0],
-            in(reg) fpstate.fpcr as u64,
-            in(reg) fpstate.fpsr as u64,
-        );
-    }
+unsafe fn fpsimd_restore(state: *const fpstate) {
+    // Static assertion to ensure size is correct
+    assert_eq!(core::mem::size_of_val(&(*state).regs), 16 * 32);
+
+    core::arch::asm!(
+        "ldp     q0, q1, [{0}, #(0 * 32)]",
+        "ldp     q2, q3, [{0}, #(1 * 32)]",
+        "ldp     q4, q5, [{0}, #(2 * 32)]",
+        "ldp     q6, q7, [{0}, #(3 * 32)]",
+        "ldp     q8, q9, [{0}, #(4 * 32)]",
+        "ldp     q10, q11, [{0}, #(5 * 32)]",
+        "ldp     q12, q13, [{0}, #(6 * 32)]",
+        "ldp     q14, q15, [{0}, #(7 * 32)]",
+        "ldp     q16, q17, [{0}, #(8 * 32)]",
+        "ldp     q18, q19, [{0}, #(9 * 32)]",
+        "ldp     q20, q21, [{0}, #(10 * 32)]",
+        "ldp     q22, q23, [{0}, #(11 * 32)]",
+        "ldp     q24, q25, [{0}, #(12 * 32)]",
+        "ldp     q26, q27, [{0}, #(13 * 32)]",
+        "ldp     q28, q29, [{0}, #(14 * 32)]",
+        "ldp     q30, q31, [{0}, #(15 * 32)]",
+        in(reg) (*state).regs.as_ptr(),
+    );
 }
 
-// NOTE: no_sanitize attribute removed - not a valid Rust attribute
-fn arm64_fpu_save_state(t: &mut Thread) {
-    let fpstate_ptr = t.arch.fpstate;
-    let fpstate = unsafe { &mut *(fpstate_ptr as *mut fpstate) };
+unsafe fn fpsimd_save(state: *mut fpstate) {
+    core::arch::asm!(
+        "stp     q0, q1, [{0}, #(0 * 32)]",
+        "stp     q2, q3, [{0}, #(1 * 32)]",
+        "stp     q4, q5, [{0}, #(2 * 32)]",
+        "stp     q6, q7, [{0}, #(3 * 32)]",
+        "stp     q8, q9, [{0}, #(4 * 32)]",
+        "stp     q10, q11, [{0}, #(5 * 32)]",
+        "stp     q12, q13, [{0}, #(6 * 32)]",
+        "stp     q14, q15, [{0}, #(7 * 32)]",
+        "stp     q16, q17, [{0}, #(8 * 32)]",
+        "stp     q18, q19, [{0}, #(9 * 32)]",
+        "stp     q20, q21, [{0}, #(10 * 32)]",
+        "stp     q22, q23, [{0}, #(11 * 32)]",
+        "stp     q24, q25, [{0}, #(12 * 32)]",
+        "stp     q26, q27, [{0}, #(13 * 32)]",
+        "stp     q28, q29, [{0}, #(14 * 32)]",
+        "stp     q30, q31, [{0}, #(15 * 32)]",
+        in(reg) (*state).regs.as_mut_ptr(),
+    );
+}
 
-    LTRACEF!("cpu {}, thread {}, save fpstate {:p}\n", arch_curr_cpu_num(), t.name, fpstate_ptr);
+/// Where the Z, P and FFR registers are in a save area
+unsafe fn sve_regs(state: *const fpstate, vl: usize) -> (*mut u8, *mut u8, *mut u8) {
+    let z = (state as *mut u8).add(SVE_STATE_OFFSET);
+    let p = z.add(32 * vl);
+    let ffr = p.add(16 * (vl / 8));
+    (z, p, ffr)
+}
 
-    unsafe {
-        core::arch::asm!(
-            "stp     q0, q1, [{0}, #(0 * 32)]",
-            "stp     q2, q3, [{0}, #(1 * 32)]",
-            "stp     q4, q5, [{0}, #(2 * 32)]",
-            "stp     q6, q7, [{0}, #(3 * 32)]",
-            "stp     q8, q9, [{0}, #(4 * 32)]",
-            "stp     q10, q11, [{0}, #(5 * 32)]",
-            "stp     q12, q13, [{0}, #(6 * 32)]",
-            "stp     q14, q15, [{0}, #(7 * 32)]",
-            "stp     q16, q17, [{0}, #(8 * 32)]",
-            "stp     q18, q19, [{0}, #(9 * 32)]",
-            "stp     q20, q21, [{0}, #(10 * 32)]",
-            "stp     q22, q23, [{0}, #(11 * 32)]",
-            "stp     q24, q25, [{0}, #(12 * 32)]",
-            "stp     q26, q27, [{0}, #(13 * 32)]",
-            "stp     q28, q29, [{0}, #(14 * 32)]",
-            "stp     q30, q31, [{0}, #(15 * 32)]",
-            in(reg) &mut fpstate.regs[0],
-        );
-
-        // These are 32-bit values, but the mrs instruction always uses a
-        // 64-bit source register.
-        let mut fpcr: u64 = 0;
-        let mut fpsr: u64 = 0;
-
-        core::arch::asm!("mrs {}, fpcr", out(reg) fpcr);
-        core::arch::asm!("mrs {}, fpsr", out(reg) fpsr);
-
-        fpstate.fpcr = fpcr as u32;
-        fpstate.fpsr = fpsr as u32;
-    }
+unsafe fn sve_save(state: *mut fpstate, vl: usize) {
+    let (z, p, ffr) = sve_regs(state, vl);
+    core::arch::asm!(
+        ".arch_extension sve",
+            "str z0, [{z}, #0, mul vl]",
+            "str z1, [{z}, #1, mul vl]",
+            "str z2, [{z}, #2, mul vl]",
+            "str z3, [{z}, #3, mul vl]",
+            "str z4, [{z}, #4, mul vl]",
+            "str z5, [{z}, #5, mul vl]",
+            "str z6, [{z}, #6, mul vl]",
+            "str z7, [{z}, #7, mul vl]",
+            "str z8, [{z}, #8, mul vl]",
+            "str z9, [{z}, #9, mul vl]",
+            "str z10, [{z}, #10, mul vl]",
+            "str z11, [{z}, #11, mul vl]",
+            "str z12, [{z}, #12, mul vl]",
+            "str z13, [{z}, #13, mul vl]",
+            "str z14, [{z}, #14, mul vl]",
+            "str z15, [{z}, #15, mul vl]",
+            "str z16, [{z}, #16, mul vl]",
+            "str z17, [{z}, #17, mul vl]",
+            "str z18, [{z}, #18, mul vl]",
+            "str z19, [{z}, #19, mul vl]",
+            "str z20, [{z}, #20, mul vl]",
+            "str z21, [{z}, #21, mul vl]",
+            "str z22, [{z}, #22, mul vl]",
+            "str z23, [{z}, #23, mul vl]",
+            "str z24, [{z}, #24, mul vl]",
+            "str z25, [{z}, #25, mul vl]",
+            "str z26, [{z}, #26, mul vl]",
+            "str z27, [{z}, #27, mul vl]",
+            "str z28, [{z}, #28, mul vl]",
+            "str z29, [{z}, #29, mul vl]",
+            "str z30, [{z}, #30, mul vl]",
+            "str z31, [{z}, #31, mul vl]",
+            "str p0, [{p}, #0, mul vl]",
+            "str p1, [{p}, #1, mul vl]",
+            "str p2, [{p}, #2, mul vl]",
+            "str p3, [{p}, #3, mul vl]",
+            "str p4, [{p}, #4, mul vl]",
+            "str p5, [{p}, #5, mul vl]",
+            "str p6, [{p}, #6, mul vl]",
+            "str p7, [{p}, #7, mul vl]",
+            "str p8, [{p}, #8, mul vl]",
+            "str p9, [{p}, #9, mul vl]",
+            "str p10, [{p}, #10, mul vl]",
+            "str p11, [{p}, #11, mul vl]",
+            "str p12, [{p}, #12, mul vl]",
+            "str p13, [{p}, #13, mul vl]",
+            "str p14, [{p}, #14, mul vl]",
+            "str p15, [{p}, #15, mul vl]",
+        // FFR can only be read through a predicate register
+        "rdffr   p0.b",
+        "str     p0, [{ffr}]",
+        "ldr     p0, [{p}]",
+        z = in(reg) z,
+        p = in(reg) p,
+        ffr = in(reg) ffr,
+    );
+}
 
-    LTRACEF!("thread {}, fpcr {:x}, fpsr {:x}\n", t.name, fpstate.fpcr, fpstate.fpsr);
+unsafe fn sve_restore(state: *const fpstate, vl: usize) {
+    let (z, p, ffr) = sve_regs(state, vl);
+    core::arch::asm!(
+        ".arch_extension sve",
+        "ldr     p0, [{ffr}]",
+        "wrffr   p0.b",
+            "ldr p0, [{p}, #0, mul vl]",
+            "ldr p1, [{p}, #1, mul vl]",
+            "ldr p2, [{p}, #2, mul vl]",
+            "ldr p3, [{p}, #3, mul vl]",
+            "ldr p4, [{p}, #4, mul vl]",
+            "ldr p5, [{p}, #5, mul vl]",
+            "ldr p6, [{p}, #6, mul vl]",
+            "ldr p7, [{p}, #7, mul vl]",
+            "ldr p8, [{p}, #8, mul vl]",
+            "ldr p9, [{p}, #9, mul vl]",
+            "ldr p10, [{p}, #10, mul vl]",
+            "ldr p11, [{p}, #11, mul vl]",
+            "ldr p12, [{p}, #12, mul vl]",
+            "ldr p13, [{p}, #13, mul vl]",
+            "ldr p14, [{p}, #14, mul vl]",
+            "ldr p15, [{p}, #15, mul vl]",
+            "ldr z0, [{z}, #0, mul vl]",
+            "ldr z1, [{z}, #1, mul vl]",
+            "ldr z2, [{z}, #2, mul vl]",
+            "ldr z3, [{z}, #3, mul vl]",
+            "ldr z4, [{z}, #4, mul vl]",
+            "ldr z5, [{z}, #5, mul vl]",
+            "ldr z6, [{z}, #6, mul vl]",
+            "ldr z7, [{z}, #7, mul vl]",
+            "ldr z8, [{z}, #8, mul vl]",
+            "ldr z9, [{z}, #9, mul vl]",
+            "ldr z10, [{z}, #10, mul vl]",
+            "ldr z11, [{z}, #11, mul vl]",
+            "ldr z12, [{z}, #12, mul vl]",
+            "ldr z13, [{z}, #13, mul vl]",
+            "ldr z14, [{z}, #14, mul vl]",
+            "ldr z15, [{z}, #15, mul vl]",
+            "ldr z16, [{z}, #16, mul vl]",
+            "ldr z17, [{z}, #17, mul vl]",
+            "ldr z18, [{z}, #18, mul vl]",
+            "ldr z19, [{z}, #19, mul vl]",
+            "ldr z20, [{z}, #20, mul vl]",
+            "ldr z21, [{z}, #21, mul vl]",
+            "ldr z22, [{z}, #22, mul vl]",
+            "ldr z23, [{z}, #23, mul vl]",
+            "ldr z24, [{z}, #24, mul vl]",
+            "ldr z25, [{z}, #25, mul vl]",
+            "ldr z26, [{z}, #26, mul vl]",
+            "ldr z27, [{z}, #27, mul vl]",
+            "ldr z28, [{z}, #28, mul vl]",
+            "ldr z29, [{z}, #29, mul vl]",
+            "ldr z30, [{z}, #30, mul vl]",
+            "ldr z31, [{z}, #31, mul vl]",
+        z = in(reg) z,
+        p = in(reg) p,
+        ffr = in(reg) ffr,
+    );
 }
 
 /// Save fpu state if the thread had dirtied it and disable the fpu
 #[no_mangle]
 // NOTE: no_sanitize attribute removed - not a valid Rust attribute
-pub extern "C" fn arm64_fpu_context_switch(oldthread: *mut Thread, newthread: *mut Thread) {
-    unsafe {
-        let mut cpacr: u64;
-        core::arch::asm!("mrs {}, cpacr_el1", out(reg) cpacr);
-        
-        if is_fpu_enabled(cpacr as u32) {
-            let oldthread = &mut *oldthread;
-            LTRACEF!("saving state on thread {}\n", oldthread.name);
-
-            // save the state
-            arm64_fpu_save_state(oldthread);
-
-            // disable the fpu again
-            cpacr &= !FPU_ENABLE_MASK;
-            core::arch::asm!("msr cpacr_el1, {}", in(reg) cpacr);
-            core::arch::asm!("isb sy");
-        }
-    }
+pub extern "C" fn arm64_fpu_context_switch(oldthread: *mut thread::Thread, _newthread: *mut thread::Thread) {
+    unsafe { thread::fpu::switch_out(&*oldthread) };
 }
 
-/// Called because of a fpu instruction used exception
+/// Called because of a fpu or sve instruction used exception
+///
+/// Returns false if the thread could not be given the fpu.
 #[no_mangle]
-pub extern "C" fn arm64_fpu_exception(iframe: *mut arm64::arm64_iframe_long, exception_flags: u32) {
-    let t = thread::get_current_thread();
-    LTRACEF!("cpu {}, thread {}, flags 0x{:x}\n", arch_curr_cpu_num(), t.name, exception_flags);
-
+pub extern "C" fn arm64_fpu_exception(_iframe: *mut arm64::arm64_iframe_long, exception_flags: u32) -> bool {
     // only valid to be called if exception came from lower level
     debug_assert!((exception_flags & arm64::ARM64_EXCEPTION_FLAG_LOWER_EL) != 0);
+    debug_assert!(!arm64_fpu_enabled());
 
-    unsafe {
-        let mut cpacr: u64;
-        core::arch::asm!("mrs {}, cpacr_el1", out(reg) cpacr);
-        
-        debug_assert!(!is_fpu_enabled(cpacr as u32));
-
-        // enable the fpu
-        cpacr |= FPU_ENABLE_MASK;
-        core::arch::asm!("msr cpacr_el1, {}", in(reg) cpacr);
-        core::arch::asm!("isb sy");
-
-        // load the state from the current cpu
-        if let Some(thread) = t {
-            arm64_fpu_load_state(&*thread);
-        }
+    match thread::get_current_thread() {
+        Some(current) => thread::fpu::first_use(&current),
+        None => false,
     }
 }
 
-#[inline(always)]
-fn arch_curr_cpu_num() -> u32 {
-    unsafe { arm64::arch_curr_cpu_num() }
-}
-
-// Helper for macros
-#[inline(always)]
-fn likely(ptr: *const Thread) -> *const Thread {
-    ptr
-}
-
 // ============================================================================
 // Public API for FPU Operations
 // ============================================================================
@@ -174,47 +275,134 @@ fn likely(ptr: *const Thread) -> *const Thread {
 pub type Arm64FpuState = fpstate;
 
 /// Initialize FPU for the current CPU
+///
+/// Sets SVE to the largest vector length the CPU supports, then leaves
+/// the FPU disabled so the first use traps.
 pub fn arm64_fpu_init() {
-    // TODO: Implement FPU initialization
+    unsafe {
+        let pfr0: u64;
+        core::arch::asm!("mrs {}, id_aa64pfr0_el1", out(reg) pfr0);
+
+        if (pfr0 >> 32) & 0xf != 0 {
+            write_cpacr(read_cpacr() | FPU_ENABLE_MASK | SVE_ENABLE_MASK);
+            let vl: u64;
+            core::arch::asm!(
+                ".arch_extension sve",
+                "msr     S3_0_C1_C2_0, {len}",
+                "isb",
+                "rdvl    {vl}, #1",
+                len = in(reg) ZCR_EL1_LEN_MAX,
+                vl = out(reg) vl,
+            );
+            SVE_VL.store(vl as usize, Ordering::Relaxed);
+        }
+    }
+    arm64_fpu_disable();
+}
+
+/// SVE vector length in bytes, 0 without SVE
+pub fn arm64_sve_vl() -> usize {
+    SVE_VL.load(Ordering::Relaxed)
+}
+
+/// Bytes of a thread's save area
+pub fn arm64_fpu_state_size() -> usize {
+    match arm64_sve_vl() {
+        0 => core::mem::size_of::<fpstate>(),
+        vl => SVE_STATE_OFFSET + 32 * vl + 17 * (vl / 8),
+    }
 }
 
 /// Save FPU state to a buffer
-pub fn arm64_fpu_save(_state: *mut Arm64FpuState) {
-    // TODO: Implement FPU state save
+///
+/// # Safety
+///
+/// `state` must be [`arm64_fpu_state_size`] bytes and the FPU enabled.
+pub unsafe fn arm64_fpu_save(state: *mut Arm64FpuState) {
+    match arm64_sve_vl() {
+        0 => fpsimd_save(state),
+        vl => sve_save(state, vl),
+    }
+
+    // These are 32-bit values, but the mrs instruction always uses a
+    // 64-bit source register.
+    let fpcr: u64;
+    let fpsr: u64;
+    core::arch::asm!("mrs {}, fpcr", out(reg) fpcr);
+    core::arch::asm!("mrs {}, fpsr", out(reg) fpsr);
+
+    (*state).fpcr = fpcr as u32;
+    (*state).fpsr = fpsr as u32;
 }
 
 /// Restore FPU state from a buffer
-pub fn arm64_fpu_restore(_state: *const Arm64FpuState) {
-    // TODO: Implement FPU state restore
+///
+/// # Safety
+///
+/// As for [`arm64_fpu_save`].
+pub unsafe fn arm64_fpu_restore(state: *const Arm64FpuState) {
+    match arm64_sve_vl() {
+        0 => fpsimd_restore(state),
+        vl => sve_restore(state, vl),
+    }
+
+    core::arch::asm!(
+        "msr     fpcr, {0}",
+        "msr     fpsr, {1}",
+        in(reg) (*state).fpcr as u64,
+        in(reg) (*state).fpsr as u64,
+    );
+}
+
+/// Read V register `n` from a save area, as (low, high) halves
+///
+/// # Safety
+///
+/// `state` must be [`arm64_fpu_state_size`] bytes.
+pub unsafe fn arm64_fpu_read_v(state: *const Arm64FpuState, n: usize) -> (u64, u64) {
+    match arm64_sve_vl() {
+        0 => ((*state).regs[2 * n], (*state).regs[2 * n + 1]),
+        vl => {
+            let (z, _, _) = sve_regs(state, vl);
+            let v = z.add(n * vl) as *const u64;
+            (v.read_unaligned(), v.add(1).read_unaligned())
+        }
+    }
+}
+
+/// Write V register `n` in a save area
+///
+/// With SVE this sets the low 128 bits of Z register `n`.
+///
+/// # Safety
+///
+/// As for [`arm64_fpu_read_v`].
+pub unsafe fn arm64_fpu_write_v(state: *mut Arm64FpuState, n: usize, low: u64, high: u64) {
+    match arm64_sve_vl() {
+        0 => {
+            (*state).regs[2 * n] = low;
+            (*state).regs[2 * n + 1] = high;
+        }
+        vl => {
+            let (z, _, _) = sve_regs(state, vl);
+            let v = z.add(n * vl) as *mut u64;
+            v.write_unaligned(low);
+            v.add(1).write_unaligned(high);
+        }
+    }
 }
 
 /// Check if FPU is enabled
 pub fn arm64_fpu_enabled() -> bool {
-    unsafe {
-        let cpacr: u64;
-        core::arch::asm!("mrs {}, cpacr_el1", out(reg) cpacr);
-        is_fpu_enabled(cpacr as u32)
-    }
+    unsafe { is_fpu_enabled(read_cpacr() as u32) }
 }
 
-/// Enable FPU
+/// Enable FPU, and SVE if present
 pub fn arm64_fpu_enable() {
-    unsafe {
-        let mut cpacr: u64;
-        core::arch::asm!("mrs {}, cpacr_el1", out(reg) cpacr);
-        cpacr |= FPU_ENABLE_MASK;
-        core::arch::asm!("msr cpacr_el1, {}", in(reg) cpacr);
-        core::arch::asm!("isb sy");
-    }
+    unsafe { write_cpacr(read_cpacr() | enable_mask()) };
 }
 
-/// Disable FPU
+/// Disable FPU and SVE
 pub fn arm64_fpu_disable() {
-    unsafe {
-        let mut cpacr: u64;
-        core::arch::asm!("mrs {}, cpacr_el1", out(reg) cpacr);
-        cpacr &= !FPU_ENABLE_MASK;
-        core::arch::asm!("msr cpacr_el1, {}", in(reg) cpacr);
-        core::arch::asm!("isb sy");
-    }
-}
\ No newline at end of file
+    unsafe { write_cpacr(read_cpacr() & !(FPU_ENABLE_MASK | SVE_ENABLE_MASK)) };
+}
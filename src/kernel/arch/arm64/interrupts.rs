@@ -343,7 +343,10 @@ impl Default for int_handler_saved_state_t {
 }
 
 /// Start interrupt handler (save state)
+///
+/// Enters interrupt context; see [`crate::kernel::irq::enter`].
 pub fn int_handler_start() -> int_handler_saved_state_t {
+    crate::kernel::irq::enter();
     unsafe {
         let daif: u64;
         core::arch::asm!("mrs {}, daif", out(reg) daif);
@@ -355,8 +358,11 @@ pub fn int_handler_start() -> int_handler_saved_state_t {
 }
 
 /// Finish interrupt handler (restore state)
-pub fn int_handler_finish(_state: int_handler_saved_state_t) {
-    // TODO: Restore interrupt state if needed
+///
+/// Leaves interrupt context and returns whether the current thread should
+/// be preempted; see [`crate::kernel::irq::exit`].
+pub fn int_handler_finish(_state: int_handler_saved_state_t) -> bool {
+    crate::kernel::irq::exit()
 }
 
 // ============================================================================
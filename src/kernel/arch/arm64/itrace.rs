@@ -0,0 +1,122 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ARM64 Instruction Trace (ETE and TRBE)
+//!
+//! The Embedded Trace Extension traces program flow and the Trace Buffer
+//! Extension writes it to a physically contiguous buffer, both programmed
+//! through system registers. TRBE runs in fill mode: when the buffer is
+//! full it stops, so the buffer keeps the start of the trace. Its
+//! management interrupt isn't used; the stop is seen in TRBSR_EL1 when
+//! tracing is stopped.
+//!
+//! Memory-mapped ETMv4 with CoreSight sinks isn't supported. Registers
+//! are named by encoding so the assembler needn't know the extensions.
+
+use crate::arch::arm64::registers::{read_sysreg, write_sysreg};
+use crate::kernel::lib::itrace::*;
+use crate::kernel::percpu;
+use crate::rustux::types::PAddr;
+
+/// ID_AA64DFR0_EL1 fields
+const DFR0_TRACEVER_SHIFT: u64 = 4;
+const DFR0_TRACEBUFFER_SHIFT: u64 = 44;
+
+/// TRBIDR_EL1.P: programming the buffer is owned by a higher EL
+const TRBIDR_P: u64 = 1 << 4;
+
+/// TRBLIMITR_EL1 bits; fill mode is FM = 0
+const TRBLIMITR_E: u64 = 1 << 0;
+
+/// TRBSR_EL1 bits
+const TRBSR_S: u64 = 1 << 17;
+
+/// TRFCR_EL1 bits
+const TRFCR_E0TRE: u64 = 1 << 0;
+const TRFCR_E1TRE: u64 = 1 << 1;
+
+/// TRCPRGCTLR.EN
+const TRCPRGCTLR_EN: u64 = 1 << 0;
+
+/// TRCSTATR.IDLE
+const TRCSTATR_IDLE: u64 = 1 << 0;
+
+/// TRCVICTLR: trace when resource 1 (always true) is, started, and not
+/// in Secure state or at EL2
+const TRCVICTLR_EVENT_TRUE: u64 = 1;
+const TRCVICTLR_SSSTATUS: u64 = 1 << 9;
+const TRCVICTLR_EXLEVEL_S_ALL: u64 = 0xf << 16;
+const TRCVICTLR_EXLEVEL_NS_EL2: u64 = 1 << 22;
+
+/// Wait for the trace unit to go idle after a change of TRCPRGCTLR
+unsafe fn wait_idle(idle: bool) {
+    while (read_sysreg!("s2_1_c0_c3_0") & TRCSTATR_IDLE != 0) != idle {
+        core::hint::spin_loop();
+    }
+}
+
+pub fn arm64_itrace_supported() -> bool {
+    unsafe {
+        let dfr0 = read_sysreg!("id_aa64dfr0_el1");
+        if (dfr0 >> DFR0_TRACEVER_SHIFT) & 0xf == 0 || (dfr0 >> DFR0_TRACEBUFFER_SHIFT) & 0xf == 0 {
+            return false;
+        }
+        read_sysreg!("s3_0_c9_c11_7") & TRBIDR_P == 0
+    }
+}
+
+pub unsafe fn arm64_itrace_start(base: PAddr, size: usize, offset: usize, flags: u32) {
+    let base = base as u64;
+    let limit = base + size as u64;
+
+    // Trace buffer: TRBLIMITR_EL1, TRBBASER_EL1, TRBPTR_EL1, TRBSR_EL1
+    write_sysreg!("s3_0_c9_c11_0", 0);
+    core::arch::asm!("isb", options(nostack));
+    write_sysreg!("s3_0_c9_c11_2", base);
+    write_sysreg!("s3_0_c9_c11_1", base + offset as u64);
+    write_sysreg!("s3_0_c9_c11_3", 0);
+    write_sysreg!("s3_0_c9_c11_0", limit | TRBLIMITR_E);
+
+    // Which ELs may be traced: TRFCR_EL1
+    let mut trfcr = 0;
+    if flags & ITRACE_FLAG_USER != 0 {
+        trfcr |= TRFCR_E0TRE;
+    }
+    if flags & ITRACE_FLAG_KERNEL != 0 {
+        trfcr |= TRFCR_E1TRE;
+    }
+    write_sysreg!("s3_0_c1_c2_1", trfcr);
+
+    // Trace unit: TRCPRGCTLR, TRCCONFIGR, TRCTRACEIDR, TRCVICTLR
+    write_sysreg!("s2_1_c0_c1_0", 0);
+    core::arch::asm!("isb", options(nostack));
+    wait_idle(true);
+    write_sysreg!("s2_1_c0_c4_0", 0);
+    write_sysreg!("s2_1_c0_c0_1", percpu::current_cpu_num() as u64 + 1);
+    write_sysreg!(
+        "s2_1_c0_c0_2",
+        TRCVICTLR_EVENT_TRUE | TRCVICTLR_SSSTATUS | TRCVICTLR_EXLEVEL_S_ALL | TRCVICTLR_EXLEVEL_NS_EL2
+    );
+    write_sysreg!("s2_1_c0_c1_0", TRCPRGCTLR_EN);
+    core::arch::asm!("isb", options(nostack));
+    wait_idle(false);
+}
+
+pub unsafe fn arm64_itrace_stop() -> (usize, bool) {
+    write_sysreg!("s2_1_c0_c1_0", 0);
+    core::arch::asm!("isb", options(nostack));
+    wait_idle(true);
+
+    // TSB CSYNC drains the trace unit into the buffer
+    core::arch::asm!("hint #18", "dsb nsh", options(nostack));
+    write_sysreg!("s3_0_c9_c11_0", 0);
+    core::arch::asm!("isb", options(nostack));
+
+    let offset = read_sysreg!("s3_0_c9_c11_1") - read_sysreg!("s3_0_c9_c11_2");
+    let full = read_sysreg!("s3_0_c9_c11_3") & TRBSR_S != 0;
+    write_sysreg!("s3_0_c9_c11_3", 0);
+    (offset as usize, full)
+}
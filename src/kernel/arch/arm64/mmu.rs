@@ -34,7 +34,7 @@ use crate::vm::physmap::*;
 use crate::vm::pmm::*;
 use crate::vm::vm::*;
 use crate::vm::{vaddr_to_paddr, phys_to_virt, is_user_address};
-use crate::vm::page_table::PageTableFlags;
+use crate::vm::page_table::{count_large_mappings, MappingType, PageTableFlags};
 // Import stub types from hypervisor module
 use crate::arch::arm64::include::arch::hypervisor::{RawBitmapGeneric, FixedStorage};
 
@@ -322,6 +322,11 @@ const MMU_PTE_ATTR_PXN: u64 = 1 << 53;
 const MMU_PTE_ATTR_AF: u64 = 1 << 10;
 const MMU_PTE_ATTR_NON_GLOBAL: u64 = 1 << 11;
 
+// Contiguous hint: a run of entries mapping contiguous, equally attributed
+// memory that the TLB may cache as one entry
+const MMU_PTE_ATTR_CONTIGUOUS: u64 = 1 << 52;
+const MMU_PTE_CONTIGUOUS_ENTRIES: usize = 16;
+
 // Attribute indexes
 const MMU_PTE_ATTR_ATTR_INDEX_MASK: u64 = 0b111 << 2;
 const MMU_PTE_ATTR_STRONGLY_ORDERED: u64 = 0b000 << 2;
@@ -589,6 +594,11 @@ fn mmu_flags_to_s1_pte_attr(flags: u32) -> pte_t {
 
     if (flags & ARCH_MMU_FLAG_PERM_EXECUTE) == 0 {
         attr |= MMU_PTE_ATTR_UXN | MMU_PTE_ATTR_PXN;
+    } else if flags & ARCH_MMU_FLAG_PERM_USER != 0 {
+        // User code is never executable from EL1.
+        attr |= MMU_PTE_ATTR_PXN;
+    } else {
+        attr |= MMU_PTE_ATTR_UXN;
     }
     if flags & ARCH_MMU_FLAG_NS != 0 {
         attr |= MMU_PTE_ATTR_NON_SECURE;
@@ -597,6 +607,60 @@ fn mmu_flags_to_s1_pte_attr(flags: u32) -> pte_t {
     attr
 }
 
+// Convert generic page table flags to ARCH_MMU_FLAG_* flags.
+//
+// MAIR has no write-through index, so write-through mappings stay cached.
+// Uncached mappings are device memory, which is what drivers map them for.
+fn page_table_flags_to_mmu_flags(flags: PageTableFlags) -> u32 {
+    let bits = flags.bits();
+    let mut mmu_flags = ARCH_MMU_FLAG_PERM_READ;
+
+    if bits & PageTableFlags::Write as u64 != 0 {
+        mmu_flags |= ARCH_MMU_FLAG_PERM_WRITE;
+    }
+    if bits & PageTableFlags::User as u64 != 0 {
+        mmu_flags |= ARCH_MMU_FLAG_PERM_USER;
+    }
+    if bits & PageTableFlags::NoExecute as u64 == 0 {
+        mmu_flags |= ARCH_MMU_FLAG_PERM_EXECUTE;
+    }
+
+    if bits & (PageTableFlags::CacheDisable as u64 | PageTableFlags::Device as u64) != 0 {
+        mmu_flags |= ARCH_MMU_FLAG_UNCACHED_DEVICE;
+    } else if bits & PageTableFlags::WriteCombining as u64 != 0 {
+        mmu_flags |= ARCH_MMU_FLAG_WRITE_COMBINING;
+    } else {
+        mmu_flags |= ARCH_MMU_FLAG_CACHED;
+    }
+
+    mmu_flags
+}
+
+/// Returns true if PSTATE.PAN is in use on this system.
+#[inline]
+pub fn arm64_pan_enabled() -> bool {
+    crate::arch::arm64::feature::arm64_feature_test(crate::arch::arm64::ARM64_FEATURE_PAN)
+}
+
+// MSR PAN, #imm is emitted as a raw encoding so the assembler doesn't need
+// the ARMv8.1 extension enabled.
+
+/// Open a window in which EL1 may access EL0 pages (clear PSTATE.PAN).
+#[inline]
+pub fn arm64_user_access_begin() {
+    if arm64_pan_enabled() {
+        unsafe { asm!(".inst 0xd500409f", options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+/// Close the window opened by [`arm64_user_access_begin`] (set PSTATE.PAN).
+#[inline]
+pub fn arm64_user_access_end() {
+    if arm64_pan_enabled() {
+        unsafe { asm!(".inst 0xd500419f", options(nomem, nostack, preserves_flags)) };
+    }
+}
+
 fn s1_pte_attr_to_mmu_flags(pte: pte_t, mmu_flags: &mut u32) {
     match pte & MMU_PTE_ATTR_ATTR_INDEX_MASK {
         MMU_PTE_ATTR_STRONGLY_ORDERED => {
@@ -942,6 +1006,104 @@ impl ArmArchVmAspace {
         }
     }
 
+    /// Mapping type of a block or contiguous run at `index_shift`
+    fn large_mapping_type(index_shift: u32, page_size_shift: u32) -> MappingType {
+        if index_shift > page_size_shift {
+            MappingType::from_shift(index_shift).unwrap_or(MappingType::Page4K)
+        } else {
+            MappingType::Page64K
+        }
+    }
+
+    /// Replace the block at `page_table[index]`, which maps `block_vaddr`,
+    /// with a table of next-level entries mapping the same memory with the
+    /// same attributes
+    ///
+    /// The block is invalidated and flushed before the table goes in
+    /// (break-before-make), so no CPU can see both translations.
+    fn split_block(&mut self, block_vaddr: vaddr_t, index: vaddr_t, index_shift: u32,
+                   page_size_shift: u32, page_table: *mut pte_t) -> rx_status_t {
+        let pte = unsafe { *page_table.add(index as usize) };
+        let block_paddr = pte & MMU_PTE_OUTPUT_ADDR_MASK & !((1u64 << index_shift) - 1);
+        let attrs = pte & !(MMU_PTE_OUTPUT_ADDR_MASK | MMU_PTE_DESCRIPTOR_MASK);
+
+        let next_shift = index_shift - (page_size_shift - 3);
+        let descriptor = if next_shift > page_size_shift {
+            MMU_PTE_L012_DESCRIPTOR_BLOCK
+        } else {
+            MMU_PTE_L3_DESCRIPTOR_PAGE
+        };
+
+        let mut table_paddr: paddr_t = 0;
+        let ret = self.alloc_page_table(&mut table_paddr, page_size_shift);
+        if ret != 0 {
+            return ret;
+        }
+        let table = paddr_to_physmap(table_paddr) as *mut pte_t;
+        let entries = 1usize << (page_size_shift - 3);
+        for i in 0..entries {
+            let paddr = block_paddr + ((i as u64) << next_shift);
+            unsafe { *table.add(i) = paddr | attrs | descriptor; }
+        }
+        unsafe { __dmb(ARM_MB_ISHST); }
+
+        unsafe { *page_table.add(index as usize) = MMU_PTE_DESCRIPTOR_INVALID; }
+        unsafe { __dmb(ARM_MB_ISHST); }
+        self.flush_tlb_entry(block_vaddr, true);
+        unsafe { __dsb(ARM_MB_SY); }
+
+        unsafe { *page_table.add(index as usize) = table_paddr | MMU_PTE_L012_DESCRIPTOR_TABLE; }
+        unsafe { __dmb(ARM_MB_ISHST); }
+
+        ltrace!("split block at {:#x}, shift {} into table {:#x}\n",
+                block_vaddr, index_shift, table_paddr);
+
+        count_large_mappings(Self::large_mapping_type(index_shift, page_size_shift), -1);
+        if next_shift > page_size_shift {
+            count_large_mappings(Self::large_mapping_type(next_shift, page_size_shift), entries as i64);
+        }
+        0
+    }
+
+    /// Clear the contiguous hint from the run holding `page_table[index]`,
+    /// whose entry maps `vaddr`
+    ///
+    /// Needed before changing part of a run. Like a split, the run is
+    /// invalidated and flushed before the entries are rewritten.
+    fn break_contiguous(&mut self, vaddr: vaddr_t, index: vaddr_t, page_size_shift: u32,
+                        page_table: *mut pte_t) {
+        let first = (index as usize) & !(MMU_PTE_CONTIGUOUS_ENTRIES - 1);
+        let run_vaddr = vaddr - (((index as usize - first) as vaddr_t) << page_size_shift);
+
+        let mut run = [MMU_PTE_DESCRIPTOR_INVALID; MMU_PTE_CONTIGUOUS_ENTRIES];
+        for (i, pte) in run.iter_mut().enumerate() {
+            unsafe {
+                *pte = *page_table.add(first + i);
+                *page_table.add(first + i) = MMU_PTE_DESCRIPTOR_INVALID;
+            }
+        }
+        unsafe { __dmb(ARM_MB_ISHST); }
+        for i in 0..MMU_PTE_CONTIGUOUS_ENTRIES {
+            self.flush_tlb_entry(run_vaddr + ((i as vaddr_t) << page_size_shift), true);
+        }
+        unsafe { __dsb(ARM_MB_SY); }
+
+        for (i, pte) in run.iter().enumerate() {
+            unsafe { *page_table.add(first + i) = pte & !MMU_PTE_ATTR_CONTIGUOUS; }
+        }
+        unsafe { __dmb(ARM_MB_ISHST); }
+
+        count_large_mappings(MappingType::Page64K, -1);
+    }
+
+    /// Whether a change of `size` bytes at `page_table[index]` leaves part
+    /// of the entry's contiguous run untouched
+    fn splits_contiguous_run(pte: pte_t, index: vaddr_t, size: size_t, page_size_shift: u32) -> bool {
+        let run_size = MMU_PTE_CONTIGUOUS_ENTRIES << page_size_shift;
+        pte & MMU_PTE_ATTR_CONTIGUOUS != 0
+            && ((index as usize) % MMU_PTE_CONTIGUOUS_ENTRIES != 0 || size < run_size)
+    }
+
     // NOTE: caller must DSB afterwards to ensure TLB entries are flushed
     fn unmap_page_table(&mut self, vaddr: vaddr_t, vaddr_rel: vaddr_t,
                         size: size_t, index_shift: u32,
@@ -995,7 +1157,25 @@ impl ArmArchVmAspace {
 
                     self.free_page_table(next_page_table as *mut c_void, page_table_paddr, page_size_shift);
                 }
+            } else if index_shift > page_size_shift && chunk_size != block_size as size_t
+                && (pte & MMU_PTE_DESCRIPTOR_MASK) == MMU_PTE_L012_DESCRIPTOR_BLOCK {
+                // Only part of the block goes: split it and unmap from the table
+                if self.split_block(current_vaddr - vaddr_rem, index, index_shift,
+                                    page_size_shift, page_table) != 0 {
+                    return RX_ERR_NO_MEMORY as isize;
+                }
+                continue;
             } else if pte != 0 {
+                if index_shift > page_size_shift {
+                    count_large_mappings(Self::large_mapping_type(index_shift, page_size_shift), -1);
+                } else if Self::splits_contiguous_run(pte, index, remaining_size, page_size_shift) {
+                    self.break_contiguous(current_vaddr, index, page_size_shift, page_table);
+                } else if pte & MMU_PTE_ATTR_CONTIGUOUS != 0
+                    && (index as usize) % MMU_PTE_CONTIGUOUS_ENTRIES == 0 {
+                    // The whole run goes, one entry at a time
+                    count_large_mappings(MappingType::Page64K, -1);
+                }
+
                 ltrace!("pte {:p}[{:#x}] = 0\n", page_table, index);
                 unsafe { *page_table.add(index as usize) = MMU_PTE_DESCRIPTOR_INVALID; }
 
@@ -1035,6 +1215,7 @@ impl ArmArchVmAspace {
         let mut block_mask: vaddr_t;
         let mut pte: pte_t;
         let mut mapped_size: size_t = 0;
+        let mut contiguous_left: usize = 0;
 
         ltrace!("vaddr {:#x}, vaddr_rel {:#x}, paddr {:#x}, size {:#x}, attrs {:#x}, index shift {}, page_size_shift {}, page_table {:p}\n",
                 vaddr, vaddr_rel, paddr, size, attrs,
@@ -1081,8 +1262,22 @@ impl ArmArchVmAspace {
                 pte = paddr | attrs;
                 if index_shift > page_size_shift {
                     pte |= MMU_PTE_L012_DESCRIPTOR_BLOCK;
+                    count_large_mappings(Self::large_mapping_type(index_shift, page_size_shift), 1);
                 } else {
                     pte |= MMU_PTE_L3_DESCRIPTOR_PAGE;
+
+                    // Start a contiguous run where a whole aligned one fits
+                    let run_size = MMU_PTE_CONTIGUOUS_ENTRIES << page_size_shift;
+                    let run_mask = run_size as u64 - 1;
+                    if contiguous_left == 0 && ((vaddr_rel as u64) | paddr) & run_mask == 0
+                        && size >= run_size {
+                        contiguous_left = MMU_PTE_CONTIGUOUS_ENTRIES;
+                        count_large_mappings(MappingType::Page64K, 1);
+                    }
+                    if contiguous_left > 0 {
+                        pte |= MMU_PTE_ATTR_CONTIGUOUS;
+                        contiguous_left -= 1;
+                    }
                 }
                 if (self.flags & ARCH_ASPACE_FLAG_GUEST) == 0 {
                     pte |= MMU_PTE_ATTR_NON_GLOBAL;
@@ -1145,8 +1340,22 @@ impl ArmArchVmAspace {
                 if ret != 0 {
                     goto_err!();
                 }
+            } else if index_shift > page_size_shift && chunk_size != block_size as size_t
+                && (pte & MMU_PTE_DESCRIPTOR_MASK) == MMU_PTE_L012_DESCRIPTOR_BLOCK {
+                // Only part of the block changes: split it and protect the table
+                let ret = self.split_block(vaddr - vaddr_rem, index, index_shift,
+                                           page_size_shift, page_table);
+                if ret != 0 {
+                    return ret;
+                }
+                continue;
             } else if pte != 0 {
-                pte = (pte & !MMU_PTE_PERMISSION_MASK) | attrs;
+                if index_shift == page_size_shift
+                    && Self::splits_contiguous_run(pte, index, size, page_size_shift) {
+                    self.break_contiguous(vaddr, index, page_size_shift, page_table);
+                    unsafe { pte = *page_table.add(index as usize); }
+                }
+                pte = (pte & !MMU_PTE_PERMISSION_MASK) | attrs | (pte & MMU_PTE_ATTR_CONTIGUOUS);
                 ltrace!("pte {:p}[{:#x}] = {:#x}\n",
                         page_table, index, pte);
                 unsafe { *page_table.add(index as usize) = pte; }
@@ -1676,9 +1885,9 @@ impl ArmPageTable {
 
     /// Map a page in the page table
     pub fn map(&mut self, vaddr: usize, paddr: usize, flags: PageTableFlags) -> VmResult<()> {
-        // Convert PageTableFlags to u64 for internal use
-        let flags_bits = flags.bits();
-        // TODO: Implement map using flags_bits
+        // Memory type and permissions for the descriptor
+        let attr = mmu_flags_to_s1_pte_attr(page_table_flags_to_mmu_flags(flags));
+        // TODO: Implement map, installing paddr | attr
         Ok(())
     }
 
@@ -1690,9 +1899,9 @@ impl ArmPageTable {
 
     /// Change protection flags for a page
     pub fn protect(&mut self, vaddr: usize, flags: PageTableFlags) -> VmResult<()> {
-        // Convert PageTableFlags to u64 for internal use
-        let flags_bits = flags.bits();
-        // TODO: Implement protect using flags_bits
+        // Memory type and permissions for the descriptor
+        let attr = mmu_flags_to_s1_pte_attr(page_table_flags_to_mmu_flags(flags));
+        // TODO: Implement protect, replacing the descriptor's attributes with attr
         Ok(())
     }
 
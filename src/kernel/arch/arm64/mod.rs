@@ -21,9 +21,11 @@ pub mod exceptions_c;
 pub mod feature;
 pub mod fpu;
 pub mod interrupts;
+pub mod itrace;
 pub mod mmu;
 pub mod mp;
 pub mod periphmap;
+pub mod perfmon;
 pub mod registers;
 pub mod spinlock;
 pub mod sysreg;
@@ -106,6 +108,8 @@ pub const ARM64_FEATURE_ISA_ATOMICS: u64 = 1 << 20; // Large System Extensions
 pub const ARM64_FEATURE_ISA_LR: u64 = 1 << 21;     // LDAP0/StLR instructions
 pub const ARM64_FEATURE_ISA_FP16: u64 = 1 << 22;   // Half precision floating point
 pub const ARM64_FEATURE_ISA_BF16: u64 = 1 << 23;   // BFloat16
+pub const ARM64_FEATURE_ISA_RNDR: u64 = 1 << 24;   // Random number register
+pub const ARM64_FEATURE_PAN: u64 = 1 << 25;        // Privileged Access Never
 
 // Legacy aliases for compatibility
 pub const RX_ARM64_FEATURE_ISA_FP: u64 = ARM64_FEATURE_ISA_FP;
@@ -152,6 +156,7 @@ pub const RX_HAS_CPU_FEATURES: u64 = 1;
 // CPU feature register masks
 pub const ARM64_MMFR0_ASIDBITS_MASK: u64 = 0xF << 4;
 pub const ARM64_MMFR0_ASIDBITS_16: u64 = 0b0010 << 4;
+pub const ARM64_MMFR1_PAN_MASK: u64 = 0xF << 20;
 
 // Exception base address marker
 pub const arm64_el1_exception_base: u64 = 0;
@@ -301,9 +306,12 @@ pub fn arm64_init_percpu_early() {
 // ============================================================================
 
 /// Get hardware breakpoint count
+///
+/// ID_AA64DFR0_EL1.BRPs holds the count minus one.
 pub fn arm64_hw_breakpoint_count() -> u32 {
-    // TODO: Read from hardware
-    0
+    let dfr0: u64;
+    unsafe { core::arch::asm!("mrs {}, id_aa64dfr0_el1", out(reg) dfr0) };
+    ((dfr0 >> 12) & 0xf) as u32 + 1
 }
 
 /// Validate debug state
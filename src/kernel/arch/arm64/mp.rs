@@ -116,6 +116,11 @@ pub fn arch_mpid_to_cpu_num(cluster: u32, cpu: u32) -> cpu_num_t {
     unsafe { ARM64_CPU_MAP[cluster as usize][cpu as usize] }
 }
 
+// cluster and cpu number within cluster of a global cpu number
+pub fn arch_cpu_num_to_cluster_cpu(cpu: cpu_num_t) -> (u32, u32) {
+    unsafe { (ARM64_CPU_CLUSTER_IDS[cpu as usize], ARM64_CPU_CPU_IDS[cpu as usize]) }
+}
+
 pub fn arch_prepare_current_cpu_idle_state(idle: bool) {
     // no-op
 }
@@ -221,7 +226,10 @@ pub fn arm64_mp_cpu_hotplug(_cpu_id: u32) -> i32 {
 }
 
 /// Unplug CPU
-pub fn arm64_mp_cpu_unplug(_cpu_id: u32) -> i32 {
+pub fn arm64_mp_cpu_unplug(cpu_id: u32) -> i32 {
+    // Nothing may be scheduled here once the CPU is gone
+    crate::kernel::sched::migrate_from_cpu(cpu_id);
+
     // TODO: Implement CPU unplug
     0
 }
@@ -0,0 +1,146 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ARM64 Performance Monitors (PMUv3)
+//!
+//! Event counters are selected with PMSELR_EL0 and programmed through
+//! PMXEVTYPER_EL0 and PMXEVCNTR_EL0. They are 32 bits wide; the 64-bit
+//! counters of PMUv3.5 are not used, and the cycle counter is left to
+//! [`read_perf_counter`](crate::kernel::arch::arch_traits::ArchDebug::read_perf_counter).
+//!
+//! Overflows raise the PMU's PPI, which is unmasked on every CPU that
+//! loads counters and polled for in `arm64_irq`.
+
+use crate::arch::arm64::registers::{read_sysreg, write_sysreg};
+use crate::kernel::dev::interrupt;
+use crate::kernel::lib::perfmon::*;
+
+/// PMU overflow interrupt, the PPI the GIC and device trees reserve for it
+const PMU_IRQ: u32 = 23;
+
+/// ID_AA64DFR0_EL1.PMUVer values meaning no PMUv3
+const PMUVER_NONE: u64 = 0x0;
+const PMUVER_IMP_DEF: u64 = 0xf;
+
+/// PMCR_EL0 bits
+const PMCR_E: u64 = 1 << 0;
+const PMCR_N_SHIFT: u64 = 11;
+const PMCR_N_MASK: u64 = 0x1f;
+
+/// PMXEVTYPER_EL0 bits
+const PMEVTYPER_P: u64 = 1 << 31;
+const PMEVTYPER_U: u64 = 1 << 30;
+const PMEVTYPER_EVENT_MASK: u64 = 0xffff;
+
+/// Common architectural and microarchitectural events
+const EVENT_L1D_CACHE_REFILL: u64 = 0x03;
+const EVENT_INST_RETIRED: u64 = 0x08;
+const EVENT_BR_MIS_PRED: u64 = 0x10;
+const EVENT_CPU_CYCLES: u64 = 0x11;
+const EVENT_LL_CACHE_MISS_RD: u64 = 0x37;
+
+/// Counter width
+const COUNTER_BITS: u32 = 32;
+
+/// Whether the CPU implements PMUv3
+fn supported() -> bool {
+    let pmuver = (unsafe { read_sysreg!("id_aa64dfr0_el1") } >> 8) & 0xf;
+    pmuver != PMUVER_NONE && pmuver != PMUVER_IMP_DEF
+}
+
+/// Whether common event `event` (below 0x40) is implemented
+fn implements(event: u64) -> bool {
+    let (ceid, bit) = match event {
+        0..=31 => (unsafe { read_sysreg!("pmceid0_el0") }, event),
+        _ => (unsafe { read_sysreg!("pmceid1_el0") }, event - 32),
+    };
+    ceid & (1 << bit) != 0
+}
+
+pub fn arm64_perfmon_counter_count() -> u32 {
+    if !supported() {
+        return 0;
+    }
+    ((unsafe { read_sysreg!("pmcr_el0") } >> PMCR_N_SHIFT) & PMCR_N_MASK) as u32
+}
+
+pub fn arm64_perfmon_counter_bits() -> u32 {
+    if supported() { COUNTER_BITS } else { 0 }
+}
+
+pub fn arm64_perfmon_event(event: u32) -> Option<u64> {
+    if !supported() {
+        return None;
+    }
+    let hw = match event {
+        PERFMON_EVENT_CYCLES => EVENT_CPU_CYCLES,
+        PERFMON_EVENT_INSTRUCTIONS => EVENT_INST_RETIRED,
+        // Last-level misses where the CPU counts them, L1 data refills
+        // otherwise
+        PERFMON_EVENT_CACHE_MISSES if implements(EVENT_LL_CACHE_MISS_RD) => EVENT_LL_CACHE_MISS_RD,
+        PERFMON_EVENT_CACHE_MISSES => EVENT_L1D_CACHE_REFILL,
+        PERFMON_EVENT_BRANCH_MISSES => EVENT_BR_MIS_PRED,
+        _ => return None,
+    };
+    implements(hw).then_some(hw)
+}
+
+pub unsafe fn arm64_perfmon_init() {
+    if !supported() {
+        return;
+    }
+    interrupt::unmask_interrupt(PMU_IRQ);
+}
+
+pub unsafe fn arm64_perfmon_program(index: u32, event: u64, value: u64, flags: u32) {
+    let mut evtyper = event & PMEVTYPER_EVENT_MASK;
+    if flags & PERFMON_FLAG_USER == 0 {
+        evtyper |= PMEVTYPER_U;
+    }
+    if flags & PERFMON_FLAG_KERNEL == 0 {
+        evtyper |= PMEVTYPER_P;
+    }
+
+    write_sysreg!("pmcntenclr_el0", 1u64 << index);
+    write_sysreg!("pmselr_el0", index);
+    core::arch::asm!("isb", options(nostack));
+    write_sysreg!("pmxevtyper_el0", evtyper);
+    write_sysreg!("pmxevcntr_el0", value & 0xffff_ffff);
+    write_sysreg!("pmovsclr_el0", 1u64 << index);
+    if flags & PERFMON_FLAG_INTERRUPT != 0 {
+        write_sysreg!("pmintenset_el1", 1u64 << index);
+    } else {
+        write_sysreg!("pmintenclr_el1", 1u64 << index);
+    }
+}
+
+pub unsafe fn arm64_perfmon_enable(mask: u32) {
+    let mask = mask as u64 & 0x7fff_ffff;
+    write_sysreg!("pmcntenclr_el0", !mask & 0x7fff_ffff);
+    write_sysreg!("pmcntenset_el0", mask);
+    let pmcr = read_sysreg!("pmcr_el0");
+    if mask != 0 {
+        write_sysreg!("pmcr_el0", pmcr | PMCR_E);
+    } else {
+        write_sysreg!("pmcr_el0", pmcr & !PMCR_E);
+    }
+    core::arch::asm!("isb", options(nostack));
+}
+
+pub fn arm64_perfmon_read(index: u32) -> u64 {
+    unsafe {
+        write_sysreg!("pmselr_el0", index);
+        core::arch::asm!("isb", options(nostack));
+        read_sysreg!("pmxevcntr_el0") & 0xffff_ffff
+    }
+}
+
+pub unsafe fn arm64_perfmon_ack_overflow() -> u32 {
+    // Bit 31 is the cycle counter, which isn't used
+    let status = read_sysreg!("pmovsclr_el0") & 0x7fff_ffff;
+    write_sysreg!("pmovsclr_el0", status);
+    status as u32
+}
@@ -317,6 +317,26 @@ pub struct Arm64DebugState {
     pub hw_bps: [Arm64HwBreakpoint; MAX_HW_BREAKPOINTS],
 }
 
+/// Read a system register by name or `s<op0>_<op1>_c<n>_c<m>_<op2>`
+/// encoding
+macro_rules! read_sysreg {
+    ($reg:literal) => {{
+        let value: u64;
+        core::arch::asm!(concat!("mrs {}, ", $reg), out(reg) value, options(nomem, nostack));
+        value
+    }};
+}
+
+/// Write a system register by name or encoding
+macro_rules! write_sysreg {
+    ($reg:literal, $value:expr) => {
+        core::arch::asm!(concat!("msr ", $reg, ", {}"), in(reg) $value as u64, options(nostack));
+    };
+}
+
+pub(crate) use read_sysreg;
+pub(crate) use write_sysreg;
+
 // ARM64 hardware register bit definitions
 const ARM64_MDSCR_EL1_KDE: u32 = 1 << 13;
 const ARM64_DBGBCR_USER_MASK: u32 = 0xFFFFFFFF; // Would be defined with actual mask bits
@@ -54,8 +54,8 @@ extern "C" {
 pub fn arch_thread_initialize(t: &mut Thread, entry_point: vaddr_t) {
     // zero out the entire arch state
     // Note: ArchThread is now embedded in ArchData, so we initialize the fields
+    // The stack guard was chosen when the thread was created and is kept.
     t.arch.sp = 0;
-    t.arch.stack_guard = 0;
     t.arch.unsafe_sp = 0;
     t.arch.current_percpu_ptr = core::ptr::null_mut();
     t.arch.track_debug_state = false;
@@ -87,11 +87,6 @@ pub fn arch_thread_initialize(t: &mut Thread, entry_point: vaddr_t) {
         (*frame).lr = entry_point;
     }
 
-    // This is really a global (boot-time) constant value.
-    // But it's stored in each thread struct to satisfy the
-    // compiler ABI (TPIDR_EL1 + RX_TLS_STACK_GUARD_OFFSET).
-    t.arch.stack_guard = unsafe { get_current_thread() }.arch.stack_guard;
-
     // set the stack pointer
     t.arch.sp = frame as usize;
 
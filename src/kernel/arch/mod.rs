@@ -78,6 +78,37 @@ pub fn arch_init() {
     }
 }
 
+/// This architecture's AAL implementation
+#[cfg(target_arch = "x86_64")]
+type CurrentArch = amd64::Amd64Arch;
+
+#[cfg(target_arch = "aarch64")]
+type CurrentArch = arm64::Arm64Arch;
+
+#[cfg(target_arch = "riscv64")]
+type CurrentArch = riscv64::Riscv64Arch;
+
+/// Disable interrupts on this CPU
+///
+/// Returns the previous state for [`arch_interrupt_restore`]. Nests: each
+/// save is undone by restoring its state, innermost first.
+#[inline]
+pub fn arch_interrupt_save() -> u64 {
+    unsafe { <CurrentArch as arch_traits::ArchInterrupts>::disable_interrupts() }
+}
+
+/// Restore the interrupt state returned by [`arch_interrupt_save`]
+///
+/// # Safety
+///
+/// `state` must come from the matching [`arch_interrupt_save`] on this
+/// CPU; restoring out of order can enable interrupts inside a section
+/// that needs them off.
+#[inline]
+pub unsafe fn arch_interrupt_restore(state: u64) {
+    <CurrentArch as arch_traits::ArchInterrupts>::restore_interrupts(state)
+}
+
 // Re-export arch operations for compatibility
 #[cfg(target_arch = "aarch64")]
 pub use arm64::include::arch::arch_ops as ops;
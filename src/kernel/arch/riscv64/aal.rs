@@ -86,9 +86,13 @@ impl ArchStartup for Riscv64Arch {
 
     unsafe fn late_init() {
         // Initialize PLIC (Platform-Level Interrupt Controller)
-        // Base address would come from device tree, for now use common QEMU address
-        const PLIC_BASE: usize = 0x0C00_0000;
-        plic::plic_init(PLIC_BASE);
+        // Base address comes from the device tree, falling back to the QEMU virt address
+        const DEFAULT_PLIC_BASE: usize = 0x0C00_0000;
+        let plic_base = crate::kernel::dev::fdt::platform_info()
+            .and_then(|info| info.plic)
+            .map(|plic| plic.base as usize)
+            .unwrap_or(DEFAULT_PLIC_BASE);
+        plic::plic_init(plic_base);
 
         // Set interrupt threshold for current hart
         let current_hart = mp::riscv_get_cpu_num();
@@ -508,6 +512,16 @@ impl ArchDebug for Riscv64Arch {
     }
 }
 
+// ============= ArchPerfmon Implementation =============
+
+// No counters: the SBI PMU extension isn't supported yet
+impl ArchPerfmon for Riscv64Arch {}
+
+// ============= ArchItrace Implementation =============
+
+// No trace: RISC-V trace encoders aren't supported yet
+impl ArchItrace for Riscv64Arch {}
+
 // ============= ArchFpu Implementation =============
 
 impl ArchFpu for Riscv64Arch {
@@ -530,12 +544,16 @@ impl ArchFpu for Riscv64Arch {
     }
 
     unsafe fn enable() {
-        fpu::riscv_fpu_init();
+        fpu::riscv_fpu_enable();
     }
 
     unsafe fn disable() {
         fpu::riscv_fpu_disable();
     }
+
+    fn state_size() -> usize {
+        fpu::riscv_fpu_state_size()
+    }
 }
 
 // ============= Arch Marker Trait Implementation =============
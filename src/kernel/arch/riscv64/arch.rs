@@ -9,25 +9,15 @@
 //! This module provides architecture-specific support for RISC-V 64-bit processors.
 //! It supports both Sv39 and Sv48 page table formats.
 
-use crate::arch;
-use crate::arch::riscv64;
-use crate::arch::riscv64::feature;
-use crate::arch::riscv64::registers;
-use crate::arch::riscv64::mmu;
-use crate::arch::mp;
-use crate::arch::ops;
+use crate::arch::riscv64::page_table;
+use crate::arch::riscv64::registers::{self, csr, sie};
+use crate::arch::riscv64::sbi;
+use crate::arch::riscv64::timer;
 use crate::kernel::mp::SMP_MAX_CPUS;
-use crate::bits;
-use crate::debug;
-use crate::kernel::cmdline;
-use crate::kernel::thread::{self, Thread};
-use crate::lk::init;
-use crate::lk::main;
-use crate::platform;
-use crate::rustux::errors::*;
-use crate::rustux::types::*;
-use crate::rustux::tls::{RX_TLS_STACK_GUARD_OFFSET, RX_TLS_UNSAFE_SP_OFFSET};
-use crate::trace::*;
+use crate::kernel::thread::Thread;
+
+// Import logging macros
+use crate::log_info;
 
 /// RISC-V hart (CPU) information for SMP boot
 #[repr(C)]
@@ -82,18 +72,35 @@ pub static mut RISCV_SECONDARY_SP_LIST: [RiscvSpInfo; SMP_MAX_CPUS as usize] =
     [RiscvSpInfo { hartid: 0, sp: core::ptr::null_mut(), stack_guard: 0, unsafe_sp: core::ptr::null_mut() }; SMP_MAX_CPUS as usize];
 
 /// Architecture-specific initialization
+///
+/// Runs on the boot hart with interrupts disabled. Points the trap vector
+/// at `riscv_exception_entry`, finds out what the SBI firmware provides,
+/// and switches from bare addressing to the Sv39 kernel address space.
 pub fn arch_early_init() {
-    // TODO: Implement RISC-V early initialization
-    // - Detect CPU features (extensions, ISA version)
-    // - Initialize timer
-    // - Set up interrupt controller (PLIC)
+    unsafe {
+        registers::write_csr(csr::STVEC, riscv_exception_entry as usize as u64);
+        registers::write_csr(csr::SSCRATCH, 0);
+    }
+
+    sbi::sbi_init();
+    let (major, minor) = sbi::sbi_spec_version();
+    log_info!("RISC-V: hart {} in S-mode, SBI v{}.{}", arch_curr_hartid(), major, minor);
+
+    page_table::init_kernel_as();
+    let kernel_as = page_table::kernel_as();
+    kernel_as.activate();
+    log_info!("RISC-V: Sv39 paging enabled, satp {:#x}", kernel_as.satp());
 }
 
 /// Architecture-specific initialization after main kernel init
+///
+/// Unmasks supervisor software and external interrupts and starts the
+/// timer tick. Interrupts are still globally disabled in sstatus; they
+/// are taken once the scheduler enables them.
 pub fn arch_init() {
-    // TODO: Implement RISC-V late initialization
-    // - Enable all harts
-    // - Set up IPIs
+    unsafe { registers::set_csr(csr::SIE, sie::SEIE | sie::SSIE) };
+    timer::riscv_timer_init();
+    log_info!("RISC-V: timer at {} Hz", timer::riscv_timer_frequency());
 }
 
 /// Get the current hart ID
@@ -106,6 +113,12 @@ pub fn arch_curr_hartid() -> u64 {
     hartid
 }
 
+// Defined in exceptions.S
+extern "C" {
+    /// Entry point for every trap taken in S-mode
+    fn riscv_exception_entry();
+}
+
 // Defined in start.S
 extern "C" {
     /// The exception vector base address
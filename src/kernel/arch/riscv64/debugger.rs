@@ -11,8 +11,10 @@
 
 
 use crate::arch::riscv64::exceptions_c::RiscvIframe;
+use crate::arch::riscv64::fpu;
 use crate::kernel::thread::{self, Thread};
 use crate::rustux::types::*;
+use crate::rustux::types::err::*;
 
 /// User-accessible flags in SSTATUS
 const X86_FLAGS_USER: u64 = 0x3F7FF; // Note: This is named for x86 compatibility
@@ -160,8 +162,8 @@ pub unsafe fn riscv_fill_in_iframe_from_gregs(
 
 /// Thread vector register state
 ///
-/// RISC-V vector extension is optional, so this is a placeholder
-/// for future support when the V extension is more widely available.
+/// `vdata` holds v0-v31 back to back, `vlen / 8` bytes each, as far as
+/// they fit.
 #[repr(C)]
 pub struct RiscvThreadStateVectorRegs {
     pub vlen: u32,  // Vector length in bits
@@ -187,13 +189,41 @@ pub struct RiscvThreadStateVectorRegs {
 ///
 /// Thread must be valid and regs must point to valid memory
 pub unsafe fn riscv_get_set_vector_regs(
-    _thread: &mut Thread,
-    _regs: *mut RiscvThreadStateVectorRegs,
-    _access: RegAccess,
+    thread: &mut Thread,
+    regs: *mut RiscvThreadStateVectorRegs,
+    access: RegAccess,
 ) -> i32 {
-    // TODO: Implement vector register support when V extension is available
-    // For now, return error as not supported
-    -1
+    let vlenb = fpu::riscv_vlenb();
+    if vlenb == 0 {
+        return RX_ERR_NOT_SUPPORTED;
+    }
+
+    let set = matches!(access, RegAccess::Set);
+    let regs = &mut *regs;
+    let done = thread::fpu::with_state(thread, set, |state| {
+        let csrs = (state as *mut u8).add(fpu::VECTOR_STATE_OFFSET) as *mut fpu::VectorCsrs;
+        let saved = core::slice::from_raw_parts_mut(
+            csrs.add(1) as *mut u8,
+            (32 * vlenb).min(regs.vdata.len()),
+        );
+        if set {
+            (*csrs).vl = regs.vl as u64;
+            (*csrs).vtype = regs.vtype as u64;
+            (*csrs).vstart = regs.vstart;
+            saved.copy_from_slice(&regs.vdata[..saved.len()]);
+        } else {
+            regs.vlen = (vlenb * 8) as u32;
+            regs.vl = (*csrs).vl as u32;
+            regs.vtype = (*csrs).vtype as u32;
+            regs.vstart = (*csrs).vstart;
+            regs.vdata[..saved.len()].copy_from_slice(saved);
+        }
+    });
+
+    match done {
+        Some(()) => RX_OK,
+        None => RX_ERR_NO_MEMORY,
+    }
 }
 
 /// Register access direction
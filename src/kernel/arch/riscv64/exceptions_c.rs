@@ -8,95 +8,26 @@
 //!
 //! This module provides exception handling for RISC-V, including
 //! page faults, illegal instructions, and system calls.
+//!
+//! Every trap enters through `riscv_exception_entry` in exceptions.S,
+//! installed in stvec in direct mode, which saves a [`RiscvIframe`] and
+//! calls [`riscv_exception_handler`]; the cause is decoded here from
+//! scause.
 
 
-use crate::arch::riscv64::registers::csr;
-use crate::arch::riscv64::registers::scause;
+use crate::arch::riscv64::fpu;
+use crate::arch::riscv64::mp;
+use crate::arch::riscv64::plic;
+use crate::arch::riscv64::registers::{self, csr, scause, sie, sstatus};
+use crate::arch::riscv64::timer;
 use crate::debug;
+use crate::kernel::lib::gdbstub;
+use crate::kernel::lib::profiler;
+use crate::kernel::object::exception;
 use crate::kernel::thread;
 use crate::print;
 use crate::rustux::types::*;
 
-/// System call numbers
-///
-/// These match the Linux RISC-V system call numbering for compatibility
-pub mod syscall_nr {
-    pub const SYS_READ: usize = 63;
-    pub const SYS_WRITE: usize = 64;
-    pub const SYS_OPEN: usize = 1024;
-    pub const SYS_CLOSE: usize = 57;
-    pub const SYS_STAT: usize = 1038;
-    pub const SYS_FSTAT: usize = 80;
-    pub const SYS_POLL: usize = 83;
-    pub const SYS_MMAP: usize = 222;
-    pub const SYS_MPROTECT: usize = 226;
-    pub const SYS_MUNMAP: usize = 215;
-    pub const SYS_BRK: usize = 214;
-    pub const SYS_RT_SIGACTION: usize = 135;
-    pub const SYS_RT_SIGPROCMASK: usize = 136;
-    pub const SYS_IOCTL: usize = 29;
-    pub const SYS_PREAD64: usize = 67;
-    pub const SYS_PWRITE64: usize = 68;
-    pub const SYS_READV: usize = 66;
-    pub const SYS_WRITEV: usize = 65;
-    pub const SYS_ACCESS: usize = 1033;
-    pub const SYS_PIPE: usize = 59;
-    pub const SYS_SELECT: usize = 52;
-    pub const SYS_SCHED_YIELD: usize = 124;
-    pub const SYS_MREMAP: usize = 216;
-    pub const SYS_MSYNC: usize = 227;
-    pub const SYS_MINCORE: usize = 232;
-    pub const SYS_MADVISE: usize = 233;
-    pub const SYS_SHMGET: usize = 194;
-    pub const SYS_SHMAT: usize = 30;
-    pub const SYS_SHMCTL: usize = 31;
-    pub const SYS_DUP: usize = 23;
-    pub const SYS_DUP2: usize = 24;
-    pub const SYS_PAUSE: usize = 235;
-    pub const SYS_NANOSLEEP: usize = 101;
-    pub const SYS_GETPID: usize = 172;
-    pub const SYS_SOCKET: usize = 198;
-    pub const SYS_CONNECT: usize = 203;
-    pub const SYS_ACCEPT: usize = 202;
-    pub const SYS_SENDTO: usize = 206;
-    pub const SYS_RECVFROM: usize = 207;
-    pub const SYS_SENDMSG: usize = 211;
-    pub const SYS_RECVMSG: usize = 212;
-    pub const SYS_SHUTDOWN: usize = 205;
-    pub const SYS_BIND: usize = 200;
-    pub const SYS_LISTEN: usize = 201;
-    pub const SYS_GETSOCKNAME: usize = 204;
-    pub const SYS_GETPEERNAME: usize = 208;
-    pub const SYS_SOCKETPAIR: usize = 199;
-    pub const SYS_SETSOCKOPT: usize = 209;
-    pub const SYS_GETSOCKOPT: usize = 210;
-    pub const SYS_CLONE: usize = 220;
-    pub const SYS_FORK: usize = 1739;
-    pub const SYS_VFORK: usize = 190;
-    pub const SYS_EXECVE: usize = 221;
-    pub const SYS_EXIT: usize = 93;
-    pub const SYS_WAIT4: usize = 61;
-    pub const SYS_KILL: usize = 129;
-    pub const SYS_UNAME: usize = 160;
-
-    // Rustux-specific system calls
-    pub const SYS_RUSTUX_DEBUG_PRINT: usize = 1000;
-    pub const SYS_RUSTUX_THREAD_CREATE: usize = 1001;
-    pub const SYS_RUSTUX_THREAD_EXIT: usize = 1002;
-    pub const SYS_RUSTUX_THREAD_YIELD: usize = 1003;
-}
-
-/// System call return values
-pub mod syscall_ret {
-    pub const OK: i64 = 0;
-    pub const ENOSYS: i64 = -38;  // Function not implemented
-    pub const EINVAL: i64 = -22;  // Invalid argument
-    pub const EPERM: i64 = -1;    // Operation not permitted
-    pub const EFAULT: i64 = -14;  // Bad address
-    pub const ENOMEM: i64 = -12;  // Out of memory
-    pub const EAGAIN: i64 = -11;  // Try again
-}
-
 /// RISC-V interrupt frame
 ///
 /// Captures the processor state at exception time
@@ -142,6 +73,15 @@ pub struct RiscvIframe {
     pub tval: u64,  // Trap value (STVAL)
 }
 
+// exceptions.S saves and restores the frame at these offsets
+const _: () = assert!(core::mem::size_of::<RiscvIframe>() == 280);
+const _: () = assert!(core::mem::offset_of!(RiscvIframe, tp) == 24);
+const _: () = assert!(core::mem::offset_of!(RiscvIframe, a0) == 72);
+const _: () = assert!(core::mem::offset_of!(RiscvIframe, s2) == 136);
+const _: () = assert!(core::mem::offset_of!(RiscvIframe, t3) == 216);
+const _: () = assert!(core::mem::offset_of!(RiscvIframe, pc) == 248);
+const _: () = assert!(core::mem::offset_of!(RiscvIframe, tval) == 272);
+
 /// Exception dispatch context
 #[repr(C)]
 pub struct ExceptionContext {
@@ -162,8 +102,8 @@ pub enum ExceptionType {
 }
 
 /// Dump the exception frame for debugging
-fn dump_iframe(iframe: &RiscvIframe) {
-    println!("RISC-V Exception Frame:");
+pub(crate) fn dump_iframe(iframe: &RiscvIframe) {
+    println!("RISC-V Exception Frame: {}", scause::description(iframe.cause));
     println!("  PC     = {:#18x}", iframe.pc);
     println!("  SSTATUS = {:#18x}", iframe.status);
     println!("  SCAUSE  = {:#18x}", iframe.cause);
@@ -189,21 +129,18 @@ fn is_from_user(iframe: &RiscvIframe) -> bool {
     (iframe.status & (1 << 8)) == 0
 }
 
-/// Fatal exception handler - prints diagnostic and halts
+/// Fatal exception handler - records the frame and panics
+///
+/// The panic path dumps the frame and a backtrace of the faulting code.
 #[cold]
 fn exception_die(iframe: &RiscvIframe, msg: &str) -> ! {
-    println!("{}", msg);
-    println!("Exception cause: {:#x}", iframe.cause);
-    if iframe.tval != 0 {
-        println!("Trap value: {:#x}", iframe.tval);
-    }
-    dump_iframe(iframe);
-
-    // TODO: platform_halt(HALT_ACTION_HALT, HALT_REASON_SW_PANIC);
-
-    loop {
-        unsafe { core::arch::asm!("wfi") };
-    }
+    crate::kernel::lib::crashlog::set_iframe(iframe as *const RiscvIframe as *mut u8);
+    panic!(
+        "{} (cause {:#x}, tval {:#x})",
+        msg.trim_end(),
+        iframe.cause,
+        iframe.tval
+    );
 }
 
 /// Page fault handler
@@ -242,6 +179,18 @@ fn riscv_page_fault_handler(iframe: &mut RiscvIframe) {
 
 /// Illegal instruction handler
 fn riscv_illegal_instruction_handler(iframe: &mut RiscvIframe) {
+    // F, D and V instructions are illegal while FS is Off, which is how
+    // threads are switched in; give the thread the FPU and retry
+    if is_from_user(iframe) && iframe.status & fpu::FS_MASK == 0 {
+        if let Some(current) = thread::get_current_thread() {
+            if thread::fpu::first_use(&current) {
+                let live = riscv_read_sstatus() & (fpu::FS_MASK | fpu::VS_MASK);
+                iframe.status = (iframe.status & !(fpu::FS_MASK | fpu::VS_MASK)) | live;
+                return;
+            }
+        }
+    }
+
     if is_from_user(iframe) {
         // TODO: Try to dispatch to user-space exception handler
         exception_die(iframe, "User illegal instruction (unimplemented)\n");
@@ -253,146 +202,90 @@ fn riscv_illegal_instruction_handler(iframe: &mut RiscvIframe) {
 /// Breakpoint handler
 fn riscv_breakpoint_handler(iframe: &mut RiscvIframe) {
     if is_from_user(iframe) {
-        // TODO: Try to dispatch to user-space exception handler
-        exception_die(iframe, "User breakpoint (unimplemented)\n");
-    } else {
+        if let Some(event) = exception::DebugEvent::from_scause(iframe.cause) {
+            let regs = general_regs(iframe);
+
+            // The thread blocks until its handler is done with it
+            unsafe { registers::set_csr(csr::SSTATUS, sstatus::SIE) };
+            let handled = exception::dispatch_debug(event, &regs, 0, iframe.cause);
+            unsafe { registers::clear_csr(csr::SSTATUS, sstatus::SIE) };
+            if handled {
+                return;
+            }
+        }
+        exception_die(iframe, "User breakpoint with no exception handler\n");
+    } else if !gdbstub::handle_exception(iframe, gdbstub::SIGTRAP) {
         exception_die(iframe, "Kernel breakpoint\n");
     }
 }
 
+/// Registers of the user thread that took the exception, x0-x31
+fn general_regs(iframe: &RiscvIframe) -> exception::GeneralRegs {
+    exception::GeneralRegs {
+        pc: iframe.pc,
+        sp: iframe.sp,
+        fp: iframe.s0,
+        flags: iframe.status,
+        regs: [
+            0, iframe.ra, iframe.sp, iframe.gp, iframe.tp, iframe.t0, iframe.t1, iframe.t2,
+            iframe.s0, iframe.s1, iframe.a0, iframe.a1, iframe.a2, iframe.a3, iframe.a4, iframe.a5,
+            iframe.a6, iframe.a7, iframe.s2, iframe.s3, iframe.s4, iframe.s5, iframe.s6, iframe.s7,
+            iframe.s8, iframe.s9, iframe.s10, iframe.s11, iframe.t3, iframe.t4, iframe.t5, iframe.t6,
+        ],
+    }
+}
+
 /// Supervisor software interrupt (IPI from other harts)
+///
+/// SBI IPIs carry no type, so both mailboxes are checked.
 fn riscv_software_interrupt_handler(_iframe: &mut RiscvIframe) {
-    // TODO: Handle inter-processor interrupt
-    // Typically used for TLB shootdowns, rescheduling, etc.
-    println!("Software interrupt received");
+    unsafe { registers::clear_csr(csr::SIP, sie::SSIE) };
+
+    crate::kernel::mp::mp_mbx_generic_irq();
+    crate::kernel::mp::mp_mbx_reschedule_irq();
 }
 
 /// Supervisor timer interrupt
-fn riscv_timer_interrupt_handler(_iframe: &mut RiscvIframe) {
-    // TODO: Handle timer interrupt
-    // Typically used for preemption, timeout, etc.
-    println!("Timer interrupt received");
+fn riscv_timer_interrupt_handler(iframe: &mut RiscvIframe) {
+    profiler::sample(iframe.pc, iframe.s0, is_from_user(iframe));
+    timer::riscv_timer_interrupt();
 }
 
 /// Supervisor external interrupt (PLIC)
 fn riscv_external_interrupt_handler(_iframe: &mut RiscvIframe) {
-    // TODO: Read from PLIC to determine which device interrupted
-    println!("External interrupt received");
-}
+    let hart = mp::riscv_get_hart_id() as u32;
 
-/// Dispatch a system call to its handler
-///
-/// # Arguments
-///
-/// * `nr` - System call number
-/// * `a0`-`a5` - System call arguments
-///
-/// # Returns
-///
-/// The system call return value (negative for errors)
-fn dispatch_syscall(
-    nr: usize,
-    a0: u64,
-    a1: u64,
-    a2: u64,
-    a3: u64,
-    _a4: u64,
-    _a5: u64,
-) -> i64 {
-    match nr {
-        // Basic I/O
-        syscall_nr::SYS_READ => sys_read(a0, a1, a2),
-        syscall_nr::SYS_WRITE => sys_write(a0, a1, a2),
-
-        // Process management
-        syscall_nr::SYS_EXIT => sys_exit(a0 as i32),
-        syscall_nr::SYS_GETPID => sys_getpid(),
-
-        // Thread management (Rustux-specific)
-        syscall_nr::SYS_RUSTUX_THREAD_YIELD => sys_thread_yield(),
-
-        // Debug output (Rustux-specific)
-        syscall_nr::SYS_RUSTUX_DEBUG_PRINT => sys_debug_print(a0, a1),
-
-        // TODO: Implement more system calls
-        _ => {
-            // Unimplemented system call
-            syscall_ret::ENOSYS
+    loop {
+        let irq = unsafe { plic::plic_claim(hart) };
+        if irq == 0 {
+            break;
         }
-    }
-}
 
-// ============================================================================
-// System Call Implementations
-// ============================================================================
-
-/// Read from a file descriptor
-fn sys_read(_fd: u64, _buf: u64, _count: u64) -> i64 {
-    // TODO: Implement proper file I/O
-    syscall_ret::ENOSYS
-}
+        crate::kernel::lib::crypto::entropy::add_interrupt_jitter(irq);
+        crate::kernel::irq::record_vector(irq);
 
-/// Write to a file descriptor
-fn sys_write(fd: u64, _buf: u64, count: u64) -> i64 {
-    // fd 1 is stdout, fd 2 is stderr
-    if fd == 1 || fd == 2 {
-        // TODO: Validate user pointer and copy data
-        // For now, just return the count as if we wrote it
-        return count as i64;
+        unsafe { plic::plic_complete(hart, irq) };
     }
-
-    syscall_ret::EINVAL
-}
-
-/// Exit the current process
-fn sys_exit(_code: i32) -> ! {
-    // TODO: Implement proper process exit
-    // For now, just halt
-    loop {
-        unsafe { core::arch::asm!("wfi") };
-    }
-}
-
-/// Get process ID
-fn sys_getpid() -> i64 {
-    // TODO: Implement proper process management
-    // For now, return 1 (init process)
-    1
-}
-
-/// Yield the current thread
-fn sys_thread_yield() -> i64 {
-    // TODO: Implement proper thread yielding
-    syscall_ret::OK
-}
-
-/// Debug print (Rustux-specific)
-///
-/// Prints a null-terminated string from user space
-fn sys_debug_print(_str: u64, _len: u64) -> i64 {
-    // TODO: Validate user pointer and safely read string
-    // For now, return success
-    syscall_ret::OK
 }
 
 /// Environment call from user mode (syscall)
 fn riscv_syscall_handler(iframe: &mut RiscvIframe) {
-    // Syscall number is in a7
-    // Arguments are in a0-a6
-    // Return value goes in a0
-
-    let syscall_num = iframe.a7 as usize;
-    let arg0 = iframe.a0;
-    let arg1 = iframe.a1;
-    let arg2 = iframe.a2;
-    let arg3 = iframe.a3;
-    let arg4 = iframe.a4;
-    let arg5 = iframe.a5;
-
-    // Dispatch the system call
-    let result = dispatch_syscall(syscall_num, arg0, arg1, arg2, arg3, arg4, arg5);
-
-    // Return value goes in a0
+    // sepc points at the ecall; return past it
+    iframe.pc += 4;
+
+    // Interrupts are enabled for the call and off again for the return
+    unsafe { registers::set_csr(csr::SSTATUS, sstatus::SIE) };
+    let result = crate::kernel::syscalls::riscv_syscall_entry(
+        iframe.a0 as usize,
+        iframe.a1 as usize,
+        iframe.a2 as usize,
+        iframe.a3 as usize,
+        iframe.a4 as usize,
+        iframe.a5 as usize,
+        iframe.a7 as u32,
+    );
+    unsafe { registers::clear_csr(csr::SSTATUS, sstatus::SIE) };
+
     iframe.a0 = result as u64;
 }
 
@@ -415,11 +308,8 @@ pub unsafe extern "C" fn riscv_exception_handler(iframe: *mut RiscvIframe) {
     let cause = iframe.cause;
 
     // Check if it's an interrupt (high bit set)
-    if cause & scause::INTERRUPT_BIT != 0 {
-        // It's an interrupt
-        let interrupt_code = cause & !scause::INTERRUPT_BIT;
-
-        match interrupt_code {
+    if scause::is_interrupt(cause) {
+        match cause {
             scause::SUPERVISOR_SOFTWARE_INTERRUPT => {
                 riscv_software_interrupt_handler(iframe);
             }
@@ -430,7 +320,7 @@ pub unsafe extern "C" fn riscv_exception_handler(iframe: *mut RiscvIframe) {
                 riscv_external_interrupt_handler(iframe);
             }
             _ => {
-                println!("Unknown interrupt: {:#x}", interrupt_code);
+                println!("Unknown interrupt: {:#x}", cause & !scause::INTERRUPT_BIT);
             }
         }
     } else {
@@ -26,6 +26,7 @@ pub mod extensions {
     pub const RISCV_ISA_EXT_ZICBOZ: u64 = 1 << 10; // Cache-block zero
     pub const RISCV_ISA_EXT_ZIHINTPAUSE: u64 = 1 << 11; // Pause hint
     pub const RISCV_ISA_EXT_SSTC: u64 = 1 << 12; // Supervisor-mode timer CSRs
+    pub const RISCV_ISA_EXT_SVPBMT: u64 = 1 << 13; // Page-based memory types
 }
 
 /// Global feature flags
@@ -8,11 +8,18 @@
 //!
 //! This module provides functions for saving and restoring
 //! floating point state during context switches.
+//!
+//! Threads run with SSTATUS.FS (and VS, with the V extension) Off, so
+//! their first F, D or V instruction raises an illegal instruction
+//! exception and the handler gives them the FPU through
+//! [`thread::fpu`](crate::kernel::thread::fpu). With V, the save area is
+//! [`FpuState`] followed by [`VectorCsrs`] and v0-v31.
 
 
 use crate::arch::riscv64::registers;
 use crate::arch::riscv64::registers::csr;
 use crate::rustux::types::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// FPU register state
 ///
@@ -52,20 +59,156 @@ pub const FS_OFF: u64 = 0x0 << 13;
 pub const FS_INITIAL: u64 = 0x1 << 13;
 pub const FS_CLEAN: u64 = 0x2 << 13;
 pub const FS_DIRTY: u64 = 0x3 << 13;
+pub const FS_MASK: u64 = 0x3 << 13;
+
+/// VS (Vector Status) bits in SSTATUS, same encoding as FS
+pub const VS_INITIAL: u64 = 0x1 << 9;
+pub const VS_CLEAN: u64 = 0x2 << 9;
+pub const VS_MASK: u64 = 0x3 << 9;
+
+/// Vector CSRs in a save area, followed by v0-v31
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct VectorCsrs {
+    pub vstart: u64,
+    pub vl: u64,
+    pub vtype: u64,
+    pub vcsr: u64,
+}
+
+/// Offset of the vector state in a save area
+pub const VECTOR_STATE_OFFSET: usize = core::mem::size_of::<FpuState>();
+
+/// Bytes per vector register, 0 without the V extension
+static VLENB: AtomicUsize = AtomicUsize::new(0);
+
+unsafe fn set_status_fields(mask: u64, value: u64) {
+    let mut sstatus = registers::read_csr(csr::SSTATUS);
+    sstatus &= !mask;
+    sstatus |= value;
+    registers::write_csr(csr::SSTATUS, sstatus);
+}
 
 /// Initialize the FPU for the current hart
 ///
-/// Enables the FPU by setting the FS field to Initial in SSTATUS
+/// Probes for the V extension, whose VS field reads as zero when it is
+/// absent, then turns FP and vector access Off so the first use traps.
 pub fn riscv_fpu_init() {
     unsafe {
-        // Set FS to Initial to enable FPU access
-        let mut sstatus = registers::read_csr(csr::SSTATUS);
-        sstatus &= !(0x3 << 13); // Clear FS field
-        sstatus |= FS_INITIAL;   // Set to Initial
-        registers::write_csr(csr::SSTATUS, sstatus);
+        set_status_fields(VS_MASK, VS_INITIAL);
+        if registers::read_csr(csr::SSTATUS) & VS_MASK != 0 {
+            let vlenb: usize;
+            core::arch::asm!(
+                ".option push",
+                ".option arch, +v",
+                "csrr {0}, vlenb",
+                ".option pop",
+                out(reg) vlenb,
+                options(nostack)
+            );
+            VLENB.store(vlenb, Ordering::Relaxed);
+        }
+    }
+    riscv_fpu_disable();
+}
+
+/// Bytes per vector register, 0 without the V extension
+pub fn riscv_vlenb() -> usize {
+    VLENB.load(Ordering::Relaxed)
+}
+
+/// Bytes of a thread's save area
+pub fn riscv_fpu_state_size() -> usize {
+    match riscv_vlenb() {
+        0 => core::mem::size_of::<FpuState>(),
+        vlenb => VECTOR_STATE_OFFSET + core::mem::size_of::<VectorCsrs>() + 32 * vlenb,
+    }
+}
+
+/// Enable FP access, and vector access with the V extension
+pub fn riscv_fpu_enable() {
+    unsafe {
+        set_status_fields(FS_MASK, FS_CLEAN);
+        if riscv_vlenb() != 0 {
+            set_status_fields(VS_MASK, VS_CLEAN);
+        }
     }
 }
 
+/// Where the vector CSRs and registers are in a save area
+unsafe fn vector_regs(state: *const FpuState) -> (*mut VectorCsrs, *mut u8) {
+    let csrs = (state as *mut u8).add(VECTOR_STATE_OFFSET) as *mut VectorCsrs;
+    (csrs, csrs.add(1) as *mut u8)
+}
+
+/// Save v0-v31 and the vector CSRs, leaving the live state as it was
+unsafe fn riscv_vector_save(state: *mut FpuState, vlenb: usize) {
+    let (csrs, regs) = vector_regs(state);
+    let vstart: u64;
+    let vl: u64;
+    let vtype: u64;
+    let vcsr: u64;
+    core::arch::asm!(
+        ".option push",
+        ".option arch, +v",
+        "csrr {vstart}, vstart",
+        "csrr {vl}, vl",
+        "csrr {vtype}, vtype",
+        "csrr {vcsr}, vcsr",
+        // Whole-register stores of eight registers at a time
+        "vsetvli {tmp}, x0, e8, m8, ta, ma",
+        "vs8r.v v0, ({p})",
+        "add {p}, {p}, {stride}",
+        "vs8r.v v8, ({p})",
+        "add {p}, {p}, {stride}",
+        "vs8r.v v16, ({p})",
+        "add {p}, {p}, {stride}",
+        "vs8r.v v24, ({p})",
+        "vsetvl x0, {vl}, {vtype}",
+        "csrw vstart, {vstart}",
+        ".option pop",
+        vstart = out(reg) vstart,
+        vl = out(reg) vl,
+        vtype = out(reg) vtype,
+        vcsr = out(reg) vcsr,
+        tmp = out(reg) _,
+        p = inout(reg) regs => _,
+        stride = in(reg) 8 * vlenb,
+        options(nostack)
+    );
+    *csrs = VectorCsrs { vstart, vl, vtype, vcsr };
+}
+
+/// Load v0-v31 and the vector CSRs
+unsafe fn riscv_vector_restore(state: *const FpuState, vlenb: usize) {
+    let (csrs, regs) = vector_regs(state);
+    let csrs = *csrs;
+    core::arch::asm!(
+        ".option push",
+        ".option arch, +v",
+        "vsetvli {tmp}, x0, e8, m8, ta, ma",
+        "vl8re8.v v0, ({p})",
+        "add {p}, {p}, {stride}",
+        "vl8re8.v v8, ({p})",
+        "add {p}, {p}, {stride}",
+        "vl8re8.v v16, ({p})",
+        "add {p}, {p}, {stride}",
+        "vl8re8.v v24, ({p})",
+        "vsetvl x0, {vl}, {vtype}",
+        "csrw vstart, {vstart}",
+        "csrw vcsr, {vcsr}",
+        ".option pop",
+        tmp = out(reg) _,
+        p = inout(reg) regs => _,
+        stride = in(reg) 8 * vlenb,
+        vl = in(reg) csrs.vl,
+        vtype = in(reg) csrs.vtype,
+        vstart = in(reg) csrs.vstart,
+        vcsr = in(reg) csrs.vcsr,
+        options(nostack)
+    );
+}
+
 /// Save the current FPU state
 ///
 /// # Arguments
@@ -128,6 +271,12 @@ pub unsafe fn riscv_fpu_save(state: *mut FpuState) {
     );
     (*state).fcsr = fcsr;
 
+    let vlenb = riscv_vlenb();
+    if vlenb != 0 {
+        riscv_vector_save(state, vlenb);
+        set_status_fields(VS_MASK, VS_CLEAN);
+    }
+
     // Mark FPU as clean in SSTATUS
     let mut sstatus = registers::read_csr(csr::SSTATUS);
     sstatus &= !(0x3 << 13); // Clear FS field
@@ -194,6 +343,12 @@ pub unsafe fn riscv_fpu_restore(state: *const FpuState) {
         options(nostack)
     );
 
+    let vlenb = riscv_vlenb();
+    if vlenb != 0 {
+        riscv_vector_restore(state, vlenb);
+        set_status_fields(VS_MASK, VS_CLEAN);
+    }
+
     // Mark FPU as clean in SSTATUS
     let mut sstatus = registers::read_csr(csr::SSTATUS);
     sstatus &= !(0x3 << 13); // Clear FS field
@@ -229,12 +384,10 @@ pub fn riscv_fpu_enabled() -> bool {
 
 /// Disable the FPU
 ///
-/// Sets FS field to OFF in SSTATUS
+/// Sets the FS and VS fields to OFF in SSTATUS
 pub fn riscv_fpu_disable() {
     unsafe {
-        let mut sstatus = registers::read_csr(csr::SSTATUS);
-        sstatus &= !(0x3 << 13); // Clear FS field (sets to OFF)
-        registers::write_csr(csr::SSTATUS, sstatus);
+        set_status_fields(FS_MASK | VS_MASK, 0);
     }
 }
 
@@ -337,7 +490,7 @@ const _: () = assert!(core::mem::size_of::<FpuState>() == 264);
 
 /// Early FPU initialization (called during boot)
 ///
-/// This is called during early boot to enable FPU access
+/// This is called during early boot to set up FPU access
 pub fn riscv_fpu_early_init() {
     riscv_fpu_init();
 }
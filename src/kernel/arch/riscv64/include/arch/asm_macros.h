@@ -0,0 +1,18 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+#pragma once
+
+// Store and load a register pair relative to a base register
+.macro sdp ra, rb, base, offset
+    sd \ra, \offset(\base)
+    sd \rb, \offset + 8(\base)
+.endm
+
+.macro ldp ra, rb, base, offset
+    ld \ra, \offset(\base)
+    ld \rb, \offset + 8(\base)
+.endm
@@ -23,7 +23,7 @@ pub mod pte_flags {
     pub const ACCESSED: u64 = 1 << 6;
     pub const DIRTY: u64 = 1 << 7;
     pub const RSW: u64 = 0x3 << 8;      // Reserved for software
-    pub const PBMT: u64 = 0x3 << 59;     // Physical memory attributes
+    pub const PBMT: u64 = 0x3 << 61;     // Physical memory attributes (Svpbmt)
 }
 
 /// RISC-V Sv39/Sv48 Page Table Entry
@@ -23,6 +23,7 @@ pub mod page_table;
 pub mod periphmap;
 pub mod plic;
 pub mod registers;
+pub mod sbi;
 pub mod spinlock;
 pub mod thread;
 pub mod timer;
@@ -10,8 +10,7 @@
 //! and managing MP initialization for RISC-V systems.
 
 
-use crate::arch::riscv64::registers;
-use crate::arch::riscv64::registers::csr;
+use crate::arch::riscv64::sbi;
 use crate::debug;
 use crate::rustux::types::*;
 use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
@@ -102,10 +101,11 @@ pub fn riscv_cpu_num_to_hart_id(cpu_num: u32) -> i32 {
 ///
 /// # Returns
 ///
-/// The current hart ID from the mhartid CSR
+/// The current hart ID, kept in tp
 #[inline]
 pub fn riscv_get_hart_id() -> usize {
-    unsafe { registers::read_csr(csr::MHARTID) as usize }
+    // mhartid is M-mode only; the boot code keeps the hart ID in tp
+    crate::arch::riscv64::arch::arch_curr_hartid() as usize
 }
 
 /// Get the current CPU number
@@ -253,8 +253,8 @@ pub fn riscv_init_mp(hart_ids: &[usize], num_harts: usize) {
 
 /// Send inter-processor interrupt (IPI) to a target hart
 ///
-/// RISC-V uses MSIP (Machine Software Interrupt Pending) registers
-/// in the CLINT (Core-Local Interrupt Controller) for IPIs.
+/// The CLINT's MSIP registers are only writable from M-mode, so the
+/// supervisor software interrupt is raised through the SBI IPI extension.
 ///
 /// # Arguments
 ///
@@ -264,21 +264,10 @@ pub fn riscv_init_mp(hart_ids: &[usize], num_harts: usize) {
 ///
 /// 0 on success, negative on error
 pub fn riscv_send_ipi(target_hart_id: usize) -> i32 {
-    // MSIP registers are memory-mapped
-    // For SiFive U54/U74, MSIP base is typically at 0x0200_0000
-    // Each hart has a 4-byte MSIP register
-    const MSIP_BASE: usize = 0x0200_0000;
-
-    // Write to the MSIP register for the target hart
-    // Writing 1 triggers the software interrupt
-    let msip_addr = MSIP_BASE + (target_hart_id * 4);
-
-    unsafe {
-        let msip_ptr = msip_addr as *mut u32;
-        msip_ptr.write_volatile(1);
+    match sbi::sbi_send_ipi(1, target_hart_id as u64) {
+        Ok(()) => 0,
+        Err(status) => status,
     }
-
-    0 // OK
 }
 
 /// Wait for harts to finish booting
@@ -335,6 +324,9 @@ pub unsafe extern "C" fn riscv_secondary_hart_entry(hart_id: usize) -> ! {
 
     let cpu_num = cpu_num as u32;
 
+    // Probe vector state size and make the first FP use trap
+    crate::arch::riscv64::fpu::riscv_fpu_init();
+
     // Mark this hart as online
     riscv_mark_cpu_online(cpu_num);
 
@@ -14,8 +14,12 @@
 //! - **Sv39**: 3-level page table (512 GB address space)
 //! - **Sv48**: 4-level page table (128 TB address space)
 //! - **4KB pages**: Standard page size
-//! - **Mega pages**: 2MB pages (optional, Sv39 only)
-//! - **Giga pages**: 1GB pages (optional, Sv39 only)
+//! - **Mega pages**: 2MB leaves at level 1
+//! - **Giga pages**: 1GB leaves at level 2
+//!
+//! [`AddressSpace::map_pages`] maps a run with the largest leaves its
+//! alignment and length allow; [`AddressSpace::unmap_pages`] splits a
+//! leaf the range only partly covers.
 //!
 //! # Page Table Levels (Sv39)
 //!
@@ -40,6 +44,7 @@ use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use crate::kernel::sync::spin::SpinMutex;
 use crate::kernel::vm::{ArchPageTable, PageTableEntry as VmPageTableEntry, PageTableFlags, VmError, Result as VmResult};
+use crate::kernel::vm::page_table::{count_large_mappings, MappingType, PageUsage};
 
 /// ============================================================================
 /// Constants
@@ -63,6 +68,12 @@ pub const SV39_VA_BITS: usize = 39;
 /// Sv48 virtual address bits
 pub const SV48_VA_BITS: usize = 48;
 
+/// Physical page number field of an entry, once shifted down
+const PPN_MASK: u64 = (1 << 44) - 1;
+
+/// Index bits each level translates
+const LEVEL_BITS: usize = 9;
+
 /// ============================================================================
 /// Page Table Entry Flags
 /// ============================================================================
@@ -93,6 +104,12 @@ pub mod flags {
     /// Dirty bit (hardware set)
     pub const DIRTY: u64 = 1 << 7;
 
+    /// Svpbmt memory type: non-cacheable, idempotent (write-combining)
+    pub const PBMT_NC: u64 = 1 << 61;
+
+    /// Svpbmt memory type: non-cacheable, strongly ordered I/O
+    pub const PBMT_IO: u64 = 2 << 61;
+
     /// Read/write permission (R + W)
     pub const RW: u64 = READ | WRITE;
 
@@ -172,7 +189,14 @@ impl PageTableEntry {
 
     /// Get physical page number
     pub const fn ppn(&self) -> u64 {
-        self.entry >> 10
+        (self.entry >> 10) & PPN_MASK
+    }
+
+    /// Same entry, mapping `paddr` instead
+    pub const fn with_paddr(&self, paddr: PAddr) -> Self {
+        Self {
+            entry: (self.entry & !(PPN_MASK << 10)) | ((paddr >> 12) << 10),
+        }
     }
 
     /// Get physical address
@@ -249,6 +273,28 @@ pub struct PageTable {
     pub entries: SpinMutex<[PageTableEntry; ENTRIES_PER_PAGE_TABLE]>,
 }
 
+/// Svpbmt memory type bits for a mapping's cache policy
+///
+/// Without Svpbmt the platform's PMAs alone decide the memory type, and
+/// device ranges are already uncached there. Write-through has no Svpbmt
+/// type and keeps the PMA.
+fn pbmt_flags(flags: PageTableFlags) -> u64 {
+    use crate::arch::riscv64::feature::{extensions, has_extension};
+
+    if !has_extension(extensions::RISCV_ISA_EXT_SVPBMT) {
+        return 0;
+    }
+
+    let bits = flags.bits();
+    if bits & (PageTableFlags::CacheDisable as u64 | PageTableFlags::Device as u64) != 0 {
+        flags::PBMT_IO
+    } else if bits & PageTableFlags::WriteCombining as u64 != 0 {
+        flags::PBMT_NC
+    } else {
+        0
+    }
+}
+
 impl PageTable {
     /// Allocate a new page table
     pub fn alloc() -> Result<Self> {
@@ -268,6 +314,17 @@ impl PageTable {
         })
     }
 
+    /// View the existing page table at `paddr`, such as one an entry points to
+    pub fn from_paddr(paddr: PAddr) -> Self {
+        let vaddr = pmm::paddr_to_vaddr(paddr);
+
+        Self {
+            paddr,
+            vaddr,
+            entries: unsafe { SpinMutex::new(core::ptr::read_volatile(vaddr as *const _)) },
+        }
+    }
+
     /// Create a new page table (alias for alloc)
     pub fn new() -> Result<Self> {
         Self::alloc()
@@ -336,6 +393,7 @@ impl PageTable {
         } else {
             flags::READ | flags::VALID
         };
+        let pte_flags = pte_flags | pbmt_flags(flags);
 
         // Add user flag if user accessible
         if flags.contains(PageTableFlags::USER) {
@@ -427,6 +485,33 @@ impl PageTable {
         Ok(())
     }
 
+    /// Read the A/D bits of the page at `vaddr`, clearing them if `clear`
+    ///
+    /// Like the rest of the mapping code, this relies on the hardware
+    /// setting A and D itself (Svadu), so a cleared entry needs no fault
+    /// handling, only the `sfence.vma` that drops the cached translation.
+    pub fn harvest_wrapper(&mut self, vaddr: VAddr, clear: bool) -> Option<(PAddr, PageUsage)> {
+        let index = (vaddr >> PAGE_SHIFT) & (ENTRIES_PER_PAGE_TABLE - 1);
+        let mut entry = self.get_entry(index);
+        if !entry.is_valid() || !entry.is_leaf() {
+            return None;
+        }
+
+        let usage = PageUsage {
+            accessed: entry.entry & flags::ACCESSED != 0,
+            dirty: entry.entry & flags::DIRTY != 0,
+        };
+        if clear && (usage.accessed || usage.dirty) {
+            entry.entry &= !(flags::ACCESSED | flags::DIRTY);
+            self.set_entry(index, entry);
+            unsafe {
+                core::arch::asm!("sfence.vma {}", in(reg) vaddr, options(nostack));
+            }
+        }
+
+        Some((entry.paddr(), usage))
+    }
+
     /// Flush TLB entries (wrapper for compatibility)
     pub fn flush_tlb_wrapper(&mut self, _vaddr: Option<VAddr>) {
         // RISC-V uses sfence.vma for TLB invalidation
@@ -513,6 +598,10 @@ impl ArchPageTable for PageTable {
         self.protect_wrapper(vaddr, flags)
     }
 
+    fn harvest(&mut self, vaddr: VAddr, clear: bool) -> Option<(usize, PageUsage)> {
+        self.harvest_wrapper(vaddr, clear).map(|(paddr, usage)| (paddr as usize, usage))
+    }
+
     fn flush_tlb(&self, vaddr: Option<VAddr>) {
         // Create a mutable reference for the call
         // This is safe since flush_tlb only modifies hardware state
@@ -565,6 +654,11 @@ impl AddressSpaceMode {
     }
 }
 
+/// satp for a root table: MODE in bits 63:60, ASID in 59:44, PPN in 43:0
+pub const fn satp_value(mode: AddressSpaceMode, asid: u16, root_ppn: u64) -> u64 {
+    (mode.satp_mode() << 60) | ((asid as u64) << 44) | (root_ppn & PPN_MASK)
+}
+
 /// Virtual address space
 ///
 /// Manages the root page table for a process or the kernel.
@@ -594,11 +688,7 @@ impl AddressSpace {
     /// Get the SATP value for this address space
     pub fn satp(&self) -> u64 {
         if let Some(ref root) = self.root {
-            let mode_bits = self.mode.satp_mode() << 60;
-            let asid_bits = (self.asid as u64) << 32;
-            let ppn_bits = root.ppn();
-
-            mode_bits | asid_bits | ppn_bits
+            satp_value(self.mode, self.asid, root.ppn())
         } else {
             0
         }
@@ -741,46 +831,181 @@ impl AddressSpace {
         Ok(())
     }
 
-    /// Translate virtual address to physical address
-    pub fn translate(&self, vaddr: VAddr) -> Option<PAddr> {
+    /// Shift of the region one entry at `level` maps; level 0 maps pages
+    const fn level_shift(level: usize) -> usize {
+        PAGE_SHIFT + LEVEL_BITS * level
+    }
+
+    /// Index of `vaddr`'s entry in a table at `level`
+    const fn level_index(vaddr: VAddr, level: usize) -> usize {
+        (vaddr >> Self::level_shift(level)) & (ENTRIES_PER_PAGE_TABLE - 1)
+    }
+
+    /// Find the table at `level` that holds `vaddr`'s entry
+    ///
+    /// Missing tables are created if `alloc_tables`, otherwise the walk
+    /// fails with `RX_ERR_NOT_FOUND`. A leaf above `level` fails it with
+    /// `RX_ERR_ALREADY_EXISTS`.
+    fn table_at(&self, vaddr: VAddr, level: usize, alloc_tables: bool) -> Result<PageTable> {
+        let root = self.root.as_ref().ok_or(RX_ERR_BAD_STATE)?;
+        let mut table = PageTable::from_paddr(root.paddr());
+
+        for l in (level + 1..self.mode.levels()).rev() {
+            let index = Self::level_index(vaddr, l);
+            let entry = table.get_entry(index);
+
+            table = if !entry.is_valid() {
+                if !alloc_tables {
+                    return Err(RX_ERR_NOT_FOUND);
+                }
+                let next = PageTable::alloc()?;
+                table.set_entry(index, PageTableEntry::new_table(next.ppn()));
+                next
+            } else if entry.is_leaf() {
+                return Err(RX_ERR_ALREADY_EXISTS);
+            } else {
+                PageTable::from_paddr(entry.paddr())
+            };
+        }
+
+        Ok(table)
+    }
+
+    /// Find the entry mapping `vaddr` and the level it is at
+    ///
+    /// Returns the table holding it, so it can be changed in place. Stops
+    /// early at an invalid entry.
+    fn leaf_for(&self, vaddr: VAddr) -> Option<(PageTable, usize)> {
         let root = self.root.as_ref()?;
+        let mut table = PageTable::from_paddr(root.paddr());
 
-        match self.mode {
-            AddressSpaceMode::Sv39 => self.translate_sv39(root, vaddr),
-            AddressSpaceMode::Sv48 => self.translate_sv48(root, vaddr),
+        for level in (0..self.mode.levels()).rev() {
+            let entry = table.get_entry(Self::level_index(vaddr, level));
+            if !entry.is_valid() || entry.is_leaf() || level == 0 {
+                return Some((table, level));
+            }
+            table = PageTable::from_paddr(entry.paddr());
         }
+
+        None
     }
 
-    /// Translate virtual address in Sv39 mode
-    fn translate_sv39(&self, root: &PageTable, vaddr: VAddr) -> Option<PAddr> {
-        // Extract page table indices
-        let l2_idx = (vaddr >> 30) & 0x1FF;
-        let l1_idx = (vaddr >> 21) & 0x1FF;
-        let l0_idx = (vaddr >> 12) & 0x1FF;
+    /// Kind of mapping a leaf at `level` is
+    fn mapping_type(level: usize) -> MappingType {
+        match level {
+            0 => MappingType::Page4K,
+            1 => MappingType::Page2M,
+            _ => MappingType::Page1G,
+        }
+    }
 
-        // Walk page tables
-        let l1_entry = root.get_entry(l2_idx);
-        if !l1_entry.is_valid() {
-            return None;
+    /// Map `size` bytes at `vaddr` to `paddr`, using megapages and gigapages
+    /// where alignment and the remaining length allow
+    pub fn map_pages(&mut self, vaddr: VAddr, paddr: PAddr, size: usize, flags: u64) -> Result<()> {
+        const SUPPORTED: [MappingType; 2] = [MappingType::Page2M, MappingType::Page1G];
+
+        if (vaddr | size) & (PAGE_SIZE - 1) != 0 || paddr & ((PAGE_SIZE - 1) as u64) != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
         }
 
-        let l1_vaddr = l1_entry.paddr();
-        let l0_entry = unsafe { &*(l1_vaddr as *const PageTable) }.get_entry(l1_idx);
-        if !l0_entry.is_valid() {
-            return None;
+        let mut offset = 0;
+        while offset < size {
+            let va = vaddr + offset;
+            let pa = paddr + offset as u64;
+            let kind = MappingType::largest_fit(va, pa as usize, size - offset, &SUPPORTED);
+            let level = (kind.shift() as usize - PAGE_SHIFT) / LEVEL_BITS;
+
+            let table = self.table_at(va, level, true)?;
+            let index = Self::level_index(va, level);
+            if table.get_entry(index).is_valid() {
+                return Err(RX_ERR_ALREADY_EXISTS);
+            }
+            table.set_entry(index, PageTableEntry::new_page(pa, flags));
+            count_large_mappings(kind, 1);
+
+            offset += kind.size();
         }
 
-        let l0_vaddr = l0_entry.paddr();
-        let final_entry = unsafe { &*(l0_vaddr as *const PageTable) }.get_entry(l0_idx);
-        if !final_entry.is_valid() || !final_entry.is_leaf() {
-            return None;
+        unsafe {
+            core::arch::asm!("sfence.vma");
         }
 
-        // Calculate physical address
-        let page_paddr = final_entry.paddr();
-        let offset = vaddr & (PAGE_SIZE - 1);
+        Ok(())
+    }
 
-        Some(page_paddr + offset as u64)
+    /// Unmap `size` bytes at `vaddr`
+    ///
+    /// A megapage or gigapage only partly inside the range is split into
+    /// next-level leaves first, so the rest of it stays mapped.
+    pub fn unmap_pages(&mut self, vaddr: VAddr, size: usize) -> Result<()> {
+        if (vaddr | size) & (PAGE_SIZE - 1) != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        let end = vaddr.checked_add(size).ok_or(RX_ERR_INVALID_ARGS)?;
+        let mut va = vaddr;
+        while va < end {
+            let (table, level) = match self.leaf_for(va) {
+                Some(found) => found,
+                None => return Err(RX_ERR_BAD_STATE),
+            };
+            let index = Self::level_index(va, level);
+            let entry = table.get_entry(index);
+            let entry_size = 1usize << Self::level_shift(level);
+            let entry_start = va & !(entry_size - 1);
+            let entry_end = entry_start + entry_size;
+
+            if entry.is_valid() && level > 0 && (va != entry_start || end < entry_end) {
+                Self::split_leaf(&table, index, level)?;
+                unsafe {
+                    core::arch::asm!("sfence.vma");
+                }
+                continue;
+            }
+
+            if entry.is_valid() {
+                table.clear_entry(index);
+                count_large_mappings(Self::mapping_type(level), -1);
+            }
+            va = entry_end.min(end);
+        }
+
+        unsafe {
+            core::arch::asm!("sfence.vma");
+        }
+
+        Ok(())
+    }
+
+    /// Replace the leaf at `table[index]` with a table of next-level leaves
+    /// mapping the same memory with the same flags
+    fn split_leaf(table: &PageTable, index: usize, level: usize) -> Result<()> {
+        let entry = table.get_entry(index);
+        let next = PageTable::alloc()?;
+        let step = 1u64 << Self::level_shift(level - 1);
+
+        for i in 0..ENTRIES_PER_PAGE_TABLE {
+            next.set_entry(i, entry.with_paddr(entry.paddr() + i as u64 * step));
+        }
+        table.set_entry(index, PageTableEntry::new_table(next.ppn()));
+
+        count_large_mappings(Self::mapping_type(level), -1);
+        count_large_mappings(Self::mapping_type(level - 1), ENTRIES_PER_PAGE_TABLE as i64);
+        Ok(())
+    }
+
+    /// Translate virtual address to physical address
+    ///
+    /// Handles leaves at any level, so megapages and gigapages resolve too.
+    pub fn translate(&self, vaddr: VAddr) -> Option<PAddr> {
+        let (table, level) = self.leaf_for(vaddr)?;
+        let entry = table.get_entry(Self::level_index(vaddr, level));
+        if !entry.is_valid() || !entry.is_leaf() {
+            return None;
+        }
+
+        let offset = vaddr & ((1usize << Self::level_shift(level)) - 1);
+        Some(entry.paddr() + offset as u64)
     }
 
     // ============================================================================
@@ -901,45 +1126,6 @@ impl AddressSpace {
 
         Ok(())
     }
-
-    /// Translate virtual address in Sv48 mode
-    fn translate_sv48(&self, root: &PageTable, vaddr: VAddr) -> Option<PAddr> {
-        // Extract page table indices
-        let l3_idx = (vaddr >> 39) & 0x1FF;
-        let l2_idx = (vaddr >> 30) & 0x1FF;
-        let l1_idx = (vaddr >> 21) & 0x1FF;
-        let l0_idx = (vaddr >> 12) & 0x1FF;
-
-        // Walk page tables (4 levels for Sv48)
-        let l2_entry = root.get_entry(l3_idx);
-        if !l2_entry.is_valid() {
-            return None;
-        }
-
-        let l2_vaddr = l2_entry.paddr();
-        let l1_entry = unsafe { &*(l2_vaddr as *const PageTable) }.get_entry(l2_idx);
-        if !l1_entry.is_valid() {
-            return None;
-        }
-
-        let l1_vaddr = l1_entry.paddr();
-        let l0_entry = unsafe { &*(l1_vaddr as *const PageTable) }.get_entry(l1_idx);
-        if !l0_entry.is_valid() {
-            return None;
-        }
-
-        let l0_vaddr = l0_entry.paddr();
-        let final_entry = unsafe { &*(l0_vaddr as *const PageTable) }.get_entry(l0_idx);
-        if !final_entry.is_valid() || !final_entry.is_leaf() {
-            return None;
-        }
-
-        // Calculate physical address
-        let page_paddr = final_entry.paddr();
-        let offset = vaddr & (PAGE_SIZE - 1);
-
-        Some(page_paddr + offset as u64)
-    }
 }
 
 /// ============================================================================
@@ -949,10 +1135,67 @@ impl AddressSpace {
 /// Global kernel address space
 static mut KERNEL_AS: Option<AddressSpace> = None;
 
+/// Start of RAM on QEMU `virt`, and the end of its device region
+pub const VIRT_DRAM_BASE: u64 = 0x8000_0000;
+
+/// RAM assumed when the boot loader passed no memory map
+const VIRT_DEFAULT_DRAM_SIZE: u64 = 1 << 30;
+
+/// Granule RAM ranges are rounded out to, so they map with megapages
+const KERNEL_MAP_ALIGN: u64 = 2 * 1024 * 1024;
+
+/// Leaf flags for the kernel's mappings
+///
+/// A and D are set up front: hardware may fault instead of setting them.
+const KERNEL_MAP_FLAGS: u64 = flags::RWX | flags::GLOBAL | flags::ACCESSED | flags::DIRTY;
+
+/// The range `[base, base + length)` rounded out to `KERNEL_MAP_ALIGN`
+fn kernel_map_span(base: u64, length: u64) -> (u64, u64) {
+    let start = base & !(KERNEL_MAP_ALIGN - 1);
+    let end = (base + length + KERNEL_MAP_ALIGN - 1) & !(KERNEL_MAP_ALIGN - 1);
+    (start, end - start)
+}
+
 /// Initialize kernel address space
+///
+/// The kernel runs at its physical address and reaches physical memory
+/// through an identity physmap (`pmm::paddr_to_vaddr`), so RAM is
+/// identity mapped, along with the device region below it. Call with
+/// paging off, then [`AddressSpace::activate`] the result.
 pub fn init_kernel_as() {
+    let mut aspace = AddressSpace::new(AddressSpaceMode::Sv39, 0)
+        .expect("no memory for the kernel page table");
+
+    aspace
+        .map_pages(0, 0, VIRT_DRAM_BASE as usize, flags::RW | flags::GLOBAL | flags::ACCESSED | flags::DIRTY)
+        .expect("failed to map the device region");
+
+    let mut mapped = false;
+    if let Some(handoff) = crate::kernel::handoff::get() {
+        for range in handoff.memory_ranges() {
+            if range.mem_type == crate::kernel::handoff::MemoryType::Peripheral || range.length == 0 {
+                continue;
+            }
+            let (start, length) = kernel_map_span(range.base, range.length);
+            for page in (start..start + length).step_by(KERNEL_MAP_ALIGN as usize) {
+                // Neighbouring ranges can round out into the same megapage
+                if aspace.translate(page as VAddr).is_none() {
+                    aspace
+                        .map_pages(page as VAddr, page, KERNEL_MAP_ALIGN as usize, KERNEL_MAP_FLAGS)
+                        .expect("failed to map RAM");
+                }
+            }
+            mapped = true;
+        }
+    }
+    if !mapped {
+        aspace
+            .map_pages(VIRT_DRAM_BASE as VAddr, VIRT_DRAM_BASE, VIRT_DEFAULT_DRAM_SIZE as usize, KERNEL_MAP_FLAGS)
+            .expect("failed to map RAM");
+    }
+
     unsafe {
-        KERNEL_AS = Some(AddressSpace::new(AddressSpaceMode::Sv39, 0).unwrap());
+        KERNEL_AS = Some(aspace);
     }
 
     println!("Kernel address space initialized");
@@ -1025,4 +1268,38 @@ mod tests {
         assert!(l1_idx < 512);
         assert!(l0_idx < 512);
     }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(AddressSpace::level_shift(1), 21);
+        assert_eq!(AddressSpace::level_shift(2), 30);
+        assert_eq!(AddressSpace::level_index(0x4060_3000, 2), 1);
+        assert_eq!(AddressSpace::level_index(0x4060_3000, 1), 3);
+        assert_eq!(AddressSpace::level_index(0x4060_3000, 0), 3);
+    }
+
+    #[test]
+    fn test_satp_value() {
+        let satp = satp_value(AddressSpaceMode::Sv39, 5, 0x8_0123);
+        assert_eq!(satp >> 60, 8);
+        assert_eq!((satp >> 44) & 0xFFFF, 5);
+        assert_eq!(satp & PPN_MASK, 0x8_0123);
+        assert_eq!(satp_value(AddressSpaceMode::Sv48, 0, 1) >> 60, 9);
+    }
+
+    #[test]
+    fn test_kernel_map_span() {
+        assert_eq!(kernel_map_span(0x8020_0000, 0x10_0000), (0x8020_0000, 0x20_0000));
+        assert_eq!(kernel_map_span(0x8010_0000, 0x20_0000), (0x8000_0000, 0x40_0000));
+        assert_eq!(kernel_map_span(0x8000_0000, 0x4000_0000), (0x8000_0000, 0x4000_0000));
+    }
+
+    #[test]
+    fn test_pte_with_paddr() {
+        let pte = PageTableEntry::new_page(0x20_0000, flags::RW | flags::PBMT_NC);
+        let moved = pte.with_paddr(0x20_1000);
+        assert_eq!(moved.paddr(), 0x20_1000);
+        assert_eq!(moved.flags(), pte.flags());
+        assert_eq!(moved.entry & flags::PBMT_NC, flags::PBMT_NC);
+    }
 }
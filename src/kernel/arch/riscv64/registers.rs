@@ -24,6 +24,7 @@ pub mod csr {
     pub const SSTATUS: usize = 0x100;
     pub const SIE: usize = 0x104;
     pub const STVEC: usize = 0x105;
+    pub const SCOUNTEREN: usize = 0x106;
     pub const SSCRATCH: usize = 0x140;
     pub const SEPC: usize = 0x141;
     pub const SCAUSE: usize = 0x142;
@@ -50,12 +51,20 @@ pub mod sstatus {
     pub const SIE: u64 = 1 << 1;   // Supervisor interrupt enable
     pub const SPIE: u64 = 1 << 5;  // Supervisor previous interrupt enable
     pub const SPP: u64 = 1 << 8;   // Supervisor previous privilege mode
+    pub const VS: u64 = 0x3 << 9;  // Vector status
     pub const FS: u64 = 0x3 << 13; // Floating-point status
     pub const XS: u64 = 0x3 << 15; // Extension status
     pub const SUM: u64 = 1 << 18;  // Supervisor user memory access
     pub const MXR: u64 = 1 << 19;  // Make executable readable
 }
 
+/// sie/sip register fields
+pub mod sie {
+    pub const SSIE: u64 = 1 << 1;  // Supervisor software interrupt
+    pub const STIE: u64 = 1 << 5;  // Supervisor timer interrupt
+    pub const SEIE: u64 = 1 << 9;  // Supervisor external interrupt
+}
+
 /// scause register exception codes
 pub mod scause {
     pub const INSTRUCTION_ADDRESS_MISALIGNED: u64 = 0;
@@ -77,6 +86,35 @@ pub mod scause {
     pub const SUPERVISOR_SOFTWARE_INTERRUPT: u64 = INTERRUPT_BIT | 1;
     pub const SUPERVISOR_TIMER_INTERRUPT: u64 = INTERRUPT_BIT | 5;
     pub const SUPERVISOR_EXTERNAL_INTERRUPT: u64 = INTERRUPT_BIT | 9;
+
+    /// Whether `cause` is an interrupt rather than an exception
+    pub const fn is_interrupt(cause: u64) -> bool {
+        cause & INTERRUPT_BIT != 0
+    }
+
+    /// Human-readable name of a trap cause
+    pub fn description(cause: u64) -> &'static str {
+        match cause {
+            INSTRUCTION_ADDRESS_MISALIGNED => "instruction address misaligned",
+            INSTRUCTION_ACCESS_FAULT => "instruction access fault",
+            ILLEGAL_INSTRUCTION => "illegal instruction",
+            BREAKPOINT => "breakpoint",
+            LOAD_ADDRESS_MISALIGNED => "load address misaligned",
+            LOAD_ACCESS_FAULT => "load access fault",
+            STORE_AMO_ADDRESS_MISALIGNED => "store/AMO address misaligned",
+            STORE_AMO_ACCESS_FAULT => "store/AMO access fault",
+            ENV_CALL_FROM_U_MODE => "environment call from U-mode",
+            ENV_CALL_FROM_S_MODE => "environment call from S-mode",
+            INSTRUCTION_PAGE_FAULT => "instruction page fault",
+            LOAD_PAGE_FAULT => "load page fault",
+            STORE_AMO_PAGE_FAULT => "store/AMO page fault",
+            SUPERVISOR_SOFTWARE_INTERRUPT => "supervisor software interrupt",
+            SUPERVISOR_TIMER_INTERRUPT => "supervisor timer interrupt",
+            SUPERVISOR_EXTERNAL_INTERRUPT => "supervisor external interrupt",
+            _ if is_interrupt(cause) => "unknown interrupt",
+            _ => "unknown exception",
+        }
+    }
 }
 
 /// satp register fields (Sv39/Sv48)
@@ -88,44 +126,69 @@ pub mod satp {
     pub const ASID_BITS: u64 = 16;
 }
 
+/// Expand `$body` with `$n` bound to the CSR number `$csr` as a constant
+///
+/// The CSR instructions take the register number as an immediate, so a
+/// runtime number is matched against the supervisor CSRs the kernel uses.
+macro_rules! with_csr {
+    ($csr:expr, $n:ident => $body:expr) => {
+        match $csr {
+            csr::SSTATUS => { const $n: usize = csr::SSTATUS; $body }
+            csr::SIE => { const $n: usize = csr::SIE; $body }
+            csr::STVEC => { const $n: usize = csr::STVEC; $body }
+            csr::SCOUNTEREN => { const $n: usize = csr::SCOUNTEREN; $body }
+            csr::SSCRATCH => { const $n: usize = csr::SSCRATCH; $body }
+            csr::SEPC => { const $n: usize = csr::SEPC; $body }
+            csr::SCAUSE => { const $n: usize = csr::SCAUSE; $body }
+            csr::STVAL => { const $n: usize = csr::STVAL; $body }
+            csr::SIP => { const $n: usize = csr::SIP; $body }
+            csr::SATP => { const $n: usize = csr::SATP; $body }
+            csr::STIMECMP => { const $n: usize = csr::STIMECMP; $body }
+            other => panic!("unsupported CSR {:#x}", other),
+        }
+    };
+}
+
 /// Read a CSR
 #[inline(always)]
 pub unsafe fn read_csr(csr: usize) -> u64 {
     let value: u64;
-    core::arch::asm!(
-        "csrrs {0}, {1}, x0",
-        out(reg) value,
-        in(reg) csr,
-    );
+    with_csr!(csr, N => core::arch::asm!("csrr {0}, {1}", out(reg) value, const N));
     value
 }
 
 /// Write a CSR
 #[inline(always)]
 pub unsafe fn write_csr(csr: usize, value: u64) {
-    core::arch::asm!(
-        "csrrw x0, {0}, {1}",
-        in(reg) csr,
-        in(reg) value,
-    );
+    with_csr!(csr, N => core::arch::asm!("csrw {1}, {0}", in(reg) value, const N));
 }
 
 /// Set bits in a CSR
 #[inline(always)]
 pub unsafe fn set_csr(csr: usize, bits: u64) {
-    core::arch::asm!(
-        "csrrs x0, {0}, {1}",
-        in(reg) csr,
-        in(reg) bits,
-    );
+    with_csr!(csr, N => core::arch::asm!("csrs {1}, {0}", in(reg) bits, const N));
 }
 
 /// Clear bits in a CSR
 #[inline(always)]
 pub unsafe fn clear_csr(csr: usize, bits: u64) {
-    core::arch::asm!(
-        "csrrc x0, {0}, {1}",
-        in(reg) csr,
-        in(reg) bits,
-    );
+    with_csr!(csr, N => core::arch::asm!("csrc {1}, {0}", in(reg) bits, const N));
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scause_description() {
+        assert_eq!(scause::description(13), "load page fault");
+        assert_eq!(scause::description((1 << 63) | 5), "supervisor timer interrupt");
+        assert_eq!(scause::description((1 << 63) | 3), "unknown interrupt");
+        assert_eq!(scause::description(10), "unknown exception");
+        assert!(!scause::is_interrupt(scause::ENV_CALL_FROM_U_MODE));
+    }
 }
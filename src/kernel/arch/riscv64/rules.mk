@@ -22,6 +22,7 @@ MODULE_RUST_SRCS += \
 	$(LOCAL_DIR)/periphmap.rs \
 	$(LOCAL_DIR)/plic.rs \
 	$(LOCAL_DIR)/registers.rs \
+	$(LOCAL_DIR)/sbi.rs \
 	$(LOCAL_DIR)/spinlock.rs \
 	$(LOCAL_DIR)/thread.rs \
 	$(LOCAL_DIR)/user_copy_c.rs
@@ -66,8 +67,9 @@ GLOBAL_DEFINES += \
 	USER_ASPACE_BASE=$(USER_ASPACE_BASE) \
 	USER_ASPACE_SIZE=$(USER_ASPACE_SIZE)
 
-# Kernel base address for RISC-V 64-bit
-KERNEL_BASE := 0xffffffc000000000
+# Kernel base address for RISC-V 64-bit: SBI firmware on QEMU virt jumps
+# to 0x80200000 with paging off, and the kernel runs identity mapped
+KERNEL_BASE ?= 0x80200000
 BOOT_HEADER_SIZE ?= 0x50
 
 KERNEL_DEFINES += \
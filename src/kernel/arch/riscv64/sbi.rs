@@ -0,0 +1,350 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RISC-V Supervisor Binary Interface
+//!
+//! The kernel runs in S-mode under SBI firmware (OpenSBI on QEMU `virt`)
+//! and reaches M-mode services with `ecall`: the console before a UART
+//! driver is up, the supervisor timer, and system reset.
+//!
+//! # Design
+//!
+//! - **Probing**: [`sbi_init`] reads the specification version and probes
+//!   the extensions the kernel uses. Firmware implementing only SBI v0.1
+//!   has no base extension; everything then goes through the legacy calls.
+//! - **Console**: Written with the debug console extension (DBCN) when
+//!   present, otherwise a byte at a time with the legacy putchar call.
+//!   Newlines are sent as CR LF.
+//! - **Timer**: [`sbi_set_timer`] programs the next supervisor timer
+//!   interrupt, in `time` CSR ticks, and clears a pending one.
+
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// ============================================================================
+/// Extensions and Functions
+/// ============================================================================
+
+/// Extension IDs
+pub const EXT_BASE: u64 = 0x10;
+pub const EXT_TIME: u64 = 0x5449_4D45;
+pub const EXT_IPI: u64 = 0x73_5049;
+pub const EXT_RFENCE: u64 = 0x5246_4E43;
+pub const EXT_HSM: u64 = 0x48_534D;
+pub const EXT_SRST: u64 = 0x5352_5354;
+pub const EXT_DBCN: u64 = 0x4442_434E;
+
+/// SBI v0.1 calls, each its own extension ID
+pub const LEGACY_SET_TIMER: u64 = 0x00;
+pub const LEGACY_CONSOLE_PUTCHAR: u64 = 0x01;
+pub const LEGACY_CONSOLE_GETCHAR: u64 = 0x02;
+pub const LEGACY_SEND_IPI: u64 = 0x04;
+pub const LEGACY_SHUTDOWN: u64 = 0x08;
+
+/// Base extension functions
+const BASE_GET_SPEC_VERSION: u64 = 0;
+const BASE_GET_IMPL_ID: u64 = 1;
+const BASE_PROBE_EXTENSION: u64 = 3;
+
+/// TIME extension functions
+const TIME_SET_TIMER: u64 = 0;
+
+/// IPI extension functions
+const IPI_SEND_IPI: u64 = 0;
+
+/// DBCN extension functions
+const DBCN_CONSOLE_WRITE: u64 = 0;
+
+/// SRST extension functions and arguments
+const SRST_SYSTEM_RESET: u64 = 0;
+pub const SRST_TYPE_SHUTDOWN: u64 = 0;
+pub const SRST_TYPE_COLD_REBOOT: u64 = 1;
+pub const SRST_TYPE_WARM_REBOOT: u64 = 2;
+const SRST_REASON_NONE: u64 = 0;
+
+/// Error codes returned in a0
+pub const SBI_SUCCESS: i64 = 0;
+pub const SBI_ERR_FAILED: i64 = -1;
+pub const SBI_ERR_NOT_SUPPORTED: i64 = -2;
+pub const SBI_ERR_INVALID_PARAM: i64 = -3;
+pub const SBI_ERR_DENIED: i64 = -4;
+pub const SBI_ERR_INVALID_ADDRESS: i64 = -5;
+pub const SBI_ERR_ALREADY_AVAILABLE: i64 = -6;
+
+/// Result of an SBI call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SbiRet {
+    /// SBI error code, `SBI_SUCCESS` on success
+    pub error: i64,
+
+    /// Function-specific return value
+    pub value: u64,
+}
+
+impl SbiRet {
+    /// The value, or the error as a status
+    pub fn result(self) -> Result<u64> {
+        if self.error == SBI_SUCCESS {
+            Ok(self.value)
+        } else {
+            Err(sbi_error_to_status(self.error))
+        }
+    }
+}
+
+/// Map an SBI error code to a kernel status
+pub fn sbi_error_to_status(error: i64) -> Status {
+    match error {
+        SBI_SUCCESS => RX_OK,
+        SBI_ERR_NOT_SUPPORTED => RX_ERR_NOT_SUPPORTED,
+        SBI_ERR_INVALID_PARAM => RX_ERR_INVALID_ARGS,
+        SBI_ERR_DENIED => RX_ERR_ACCESS_DENIED,
+        SBI_ERR_INVALID_ADDRESS => RX_ERR_OUT_OF_RANGE,
+        SBI_ERR_ALREADY_AVAILABLE => RX_ERR_ALREADY_EXISTS,
+        _ => RX_ERR_INTERNAL,
+    }
+}
+
+/// Split a specification version into (major, minor)
+pub const fn decode_spec_version(version: u64) -> (u32, u32) {
+    (((version >> 24) & 0x7F) as u32, (version & 0xFF_FFFF) as u32)
+}
+
+/// Make an SBI call
+///
+/// # Safety
+///
+/// The arguments must be valid for the function; some take physical
+/// addresses the firmware reads or writes.
+#[inline]
+pub unsafe fn sbi_call(ext: u64, func: u64, arg0: u64, arg1: u64, arg2: u64) -> SbiRet {
+    let error: i64;
+    let value: u64;
+    core::arch::asm!(
+        "ecall",
+        inlateout("a0") arg0 => error,
+        inlateout("a1") arg1 => value,
+        in("a2") arg2,
+        in("a6") func,
+        in("a7") ext,
+        options(nostack)
+    );
+    SbiRet { error, value }
+}
+
+/// Make an SBI v0.1 call, which returns only a0
+#[inline]
+unsafe fn sbi_legacy_call(ext: u64, arg0: u64) -> i64 {
+    let ret: i64;
+    core::arch::asm!(
+        "ecall",
+        inlateout("a0") arg0 => ret,
+        in("a7") ext,
+        options(nostack)
+    );
+    ret
+}
+
+/// ============================================================================
+/// Probing
+/// ============================================================================
+
+/// Specification version, 0 for v0.1 firmware
+static SPEC_VERSION: AtomicU64 = AtomicU64::new(0);
+
+static HAS_TIME: AtomicBool = AtomicBool::new(false);
+static HAS_IPI: AtomicBool = AtomicBool::new(false);
+static HAS_DBCN: AtomicBool = AtomicBool::new(false);
+static HAS_SRST: AtomicBool = AtomicBool::new(false);
+
+fn probe(ext: u64) -> bool {
+    let ret = unsafe { sbi_call(EXT_BASE, BASE_PROBE_EXTENSION, ext, 0, 0) };
+    ret.error == SBI_SUCCESS && ret.value != 0
+}
+
+/// Find out what the firmware implements
+///
+/// Called once on the boot hart, before the console is used.
+pub fn sbi_init() {
+    let ret = unsafe { sbi_call(EXT_BASE, BASE_GET_SPEC_VERSION, 0, 0, 0) };
+    if ret.error != SBI_SUCCESS {
+        return;
+    }
+
+    SPEC_VERSION.store(ret.value, Ordering::Relaxed);
+    HAS_TIME.store(probe(EXT_TIME), Ordering::Relaxed);
+    HAS_IPI.store(probe(EXT_IPI), Ordering::Relaxed);
+    HAS_DBCN.store(probe(EXT_DBCN), Ordering::Relaxed);
+    HAS_SRST.store(probe(EXT_SRST), Ordering::Relaxed);
+}
+
+/// Specification version as (major, minor), (0, 1) for legacy firmware
+pub fn sbi_spec_version() -> (u32, u32) {
+    match SPEC_VERSION.load(Ordering::Relaxed) {
+        0 => (0, 1),
+        version => decode_spec_version(version),
+    }
+}
+
+/// Firmware implementation ID (1 is OpenSBI), if the base extension exists
+pub fn sbi_impl_id() -> Option<u64> {
+    if SPEC_VERSION.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    unsafe { sbi_call(EXT_BASE, BASE_GET_IMPL_ID, 0, 0, 0) }.result().ok()
+}
+
+/// Whether the debug console extension is used for output
+pub fn sbi_has_dbcn() -> bool {
+    HAS_DBCN.load(Ordering::Relaxed)
+}
+
+/// ============================================================================
+/// Timer
+/// ============================================================================
+
+/// Raise the supervisor timer interrupt when `time` reaches `deadline`
+///
+/// Also clears a pending timer interrupt; `u64::MAX` stops the timer.
+pub fn sbi_set_timer(deadline: u64) {
+    unsafe {
+        if HAS_TIME.load(Ordering::Relaxed) {
+            sbi_call(EXT_TIME, TIME_SET_TIMER, deadline, 0, 0);
+        } else {
+            sbi_legacy_call(LEGACY_SET_TIMER, deadline);
+        }
+    }
+}
+
+/// ============================================================================
+/// IPIs
+/// ============================================================================
+
+/// Raise the supervisor software interrupt on the harts in `hart_mask`,
+/// bit 0 being hart `hart_mask_base`
+pub fn sbi_send_ipi(hart_mask: u64, hart_mask_base: u64) -> Result<()> {
+    unsafe {
+        if HAS_IPI.load(Ordering::Relaxed) {
+            return sbi_call(EXT_IPI, IPI_SEND_IPI, hart_mask, hart_mask_base, 0).result().map(|_| ());
+        }
+
+        // v0.1 takes the address of an unshifted mask
+        if hart_mask_base >= 64 {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+        let mask = hart_mask << hart_mask_base;
+        match sbi_legacy_call(LEGACY_SEND_IPI, &mask as *const u64 as u64) {
+            SBI_SUCCESS => Ok(()),
+            error => Err(sbi_error_to_status(error)),
+        }
+    }
+}
+
+/// ============================================================================
+/// Console
+/// ============================================================================
+
+/// Write one byte to the firmware console
+pub fn sbi_console_putchar(c: u8) {
+    unsafe {
+        sbi_legacy_call(LEGACY_CONSOLE_PUTCHAR, c as u64);
+    }
+}
+
+/// Read a byte from the firmware console, if one is waiting
+pub fn sbi_console_getchar() -> Option<u8> {
+    let ret = unsafe { sbi_legacy_call(LEGACY_CONSOLE_GETCHAR, 0) };
+    if ret < 0 {
+        None
+    } else {
+        Some(ret as u8)
+    }
+}
+
+/// Write `bytes` as they are
+fn console_write_raw(bytes: &[u8]) {
+    if !sbi_has_dbcn() {
+        bytes.iter().for_each(|&c| sbi_console_putchar(c));
+        return;
+    }
+
+    // DBCN takes a physical address; the kernel runs identity mapped
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let ret = unsafe {
+            sbi_call(EXT_DBCN, DBCN_CONSOLE_WRITE, rest.len() as u64, rest.as_ptr() as u64, 0)
+        };
+        match ret.result() {
+            Ok(0) | Err(_) => break,
+            Ok(written) => rest = &rest[(written as usize).min(rest.len())..],
+        }
+    }
+}
+
+/// Write `s` to the firmware console, newlines as CR LF
+pub fn sbi_console_write(s: &str) {
+    let mut lines = s.split('\n');
+    if let Some(first) = lines.next() {
+        console_write_raw(first.as_bytes());
+    }
+    for line in lines {
+        console_write_raw(b"\r\n");
+        console_write_raw(line.as_bytes());
+    }
+}
+
+/// Kernel console output until a UART driver takes over
+#[allow(improper_ctypes_definitions)]
+#[no_mangle]
+pub extern "C" fn early_print(s: &str) {
+    sbi_console_write(s);
+}
+
+/// ============================================================================
+/// System Reset
+/// ============================================================================
+
+/// Shut down or reboot the machine
+///
+/// `reset_type` is one of the `SRST_TYPE_*` values. Legacy firmware can
+/// only shut down.
+pub fn sbi_system_reset(reset_type: u64) -> ! {
+    unsafe {
+        if HAS_SRST.load(Ordering::Relaxed) {
+            sbi_call(EXT_SRST, SRST_SYSTEM_RESET, reset_type, SRST_REASON_NONE, 0);
+        }
+        sbi_legacy_call(LEGACY_SHUTDOWN, 0);
+    }
+    loop {
+        unsafe { core::arch::asm!("wfi") };
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_spec_version() {
+        assert_eq!(decode_spec_version(0x0100_0000), (1, 0));
+        assert_eq!(decode_spec_version(0x0000_0002), (0, 2));
+        // Bit 31 is reserved
+        assert_eq!(decode_spec_version(0x8200_0003), (2, 3));
+    }
+
+    #[test]
+    fn test_error_to_status() {
+        assert_eq!(SbiRet { error: SBI_SUCCESS, value: 7 }.result(), Ok(7));
+        assert_eq!(sbi_error_to_status(SBI_ERR_NOT_SUPPORTED), RX_ERR_NOT_SUPPORTED);
+        assert_eq!(sbi_error_to_status(SBI_ERR_INVALID_PARAM), RX_ERR_INVALID_ARGS);
+        assert_eq!(sbi_error_to_status(-42), RX_ERR_INTERNAL);
+    }
+}
@@ -152,6 +152,10 @@ pub unsafe fn arch_context_switch(
         fn riscv_context_switch(old_sp: *mut u64, new_sp: u64);
     }
 
+    // Save FP/vector state if it was used and turn FS/VS Off, so the new
+    // thread traps on first use
+    thread::fpu::switch_out(old_thread);
+
     // TODO: This needs to be properly integrated with the Thread's arch field
     let _ = old_thread;
     let _ = new_thread;
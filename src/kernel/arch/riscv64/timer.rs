@@ -6,9 +6,45 @@
 
 //! RISC-V timer functions
 //!
-//! Provides access to the RISC-V timer (time CSR).
+//! The `time` CSR counts at the platform's timebase frequency, read from
+//! the device tree (`/cpus/timebase-frequency`, 10 MHz on QEMU `virt`).
+//! The supervisor timer interrupt is programmed through SBI; the kernel
+//! keeps it ticking every [`RISCV_TICK_NS`].
 
-use crate::rustux::types::*;
+use crate::arch::riscv64::registers::{self, csr, sie};
+use crate::arch::riscv64::sbi;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Timebase frequency used until the device tree says otherwise
+pub const RISCV_DEFAULT_TIMEBASE_HZ: u64 = 10_000_000;
+
+/// Interval between timer interrupts
+pub const RISCV_TICK_NS: u64 = 10_000_000;
+
+/// Frequency of the `time` CSR in Hz
+static TIMEBASE_HZ: AtomicU64 = AtomicU64::new(RISCV_DEFAULT_TIMEBASE_HZ);
+
+/// Convert `ticks` of a `hz` counter to nanoseconds
+pub const fn ticks_to_ns(ticks: u64, hz: u64) -> u64 {
+    (ticks as u128 * 1_000_000_000 / hz as u128) as u64
+}
+
+/// Convert nanoseconds to ticks of a `hz` counter, rounding up
+pub const fn ns_to_ticks(ns: u64, hz: u64) -> u64 {
+    ((ns as u128 * hz as u128 + 999_999_999) / 1_000_000_000) as u64
+}
+
+/// Set the timebase frequency from the device tree
+pub fn riscv_timer_set_frequency(hz: u64) {
+    if hz != 0 {
+        TIMEBASE_HZ.store(hz, Ordering::Relaxed);
+    }
+}
+
+/// Frequency of the `time` CSR in Hz
+pub fn riscv_timer_frequency() -> u64 {
+    TIMEBASE_HZ.load(Ordering::Relaxed)
+}
 
 /// Get current time from RISC-V time CSR
 ///
@@ -22,3 +58,59 @@ pub fn riscv_current_time() -> u64 {
         time
     }
 }
+
+/// Current time in nanoseconds since the counter started
+pub fn riscv_current_time_ns() -> u64 {
+    ticks_to_ns(riscv_current_time(), riscv_timer_frequency())
+}
+
+/// Raise the timer interrupt `ns` from now
+pub fn riscv_timer_set_oneshot(ns: u64) {
+    let deadline = riscv_current_time().saturating_add(ns_to_ticks(ns, riscv_timer_frequency()));
+    sbi::sbi_set_timer(deadline);
+}
+
+/// Stop the timer and clear a pending timer interrupt
+pub fn riscv_timer_cancel() {
+    sbi::sbi_set_timer(u64::MAX);
+}
+
+/// Start the periodic tick on the current hart
+pub fn riscv_timer_init() {
+    riscv_timer_set_oneshot(RISCV_TICK_NS);
+    unsafe { registers::set_csr(csr::SIE, sie::STIE) };
+}
+
+/// Handle the supervisor timer interrupt
+///
+/// Arms the next tick before running expired timers and the scheduler, so
+/// a slow tick does not stretch the interval.
+pub fn riscv_timer_interrupt() {
+    riscv_timer_set_oneshot(RISCV_TICK_NS);
+
+    let now = crate::kernel::timer::current_time();
+    crate::kernel::timer::timer_tick(now);
+    crate::kernel::sched::timer_tick();
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_conversion() {
+        // QEMU virt: 10 MHz, 100 ns per tick
+        assert_eq!(ticks_to_ns(10_000_000, 10_000_000), 1_000_000_000);
+        assert_eq!(ticks_to_ns(3, 10_000_000), 300);
+        assert_eq!(ns_to_ticks(RISCV_TICK_NS, 10_000_000), 100_000);
+        // Deadlines round up so a timer never fires early
+        assert_eq!(ns_to_ticks(150, 10_000_000), 2);
+        assert_eq!(ns_to_ticks(0, 10_000_000), 0);
+        // No overflow for counters running for years
+        assert_eq!(ticks_to_ns(u64::MAX / 1000, 1_000_000_000), u64::MAX / 1000);
+    }
+}
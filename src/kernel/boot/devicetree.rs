@@ -0,0 +1,205 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Device Tree Boot
+//!
+//! ARM64 and RISC-V firmware (QEMU virt, U-Boot, OpenSBI) enter the
+//! kernel with the physical address of a devicetree blob in a register:
+//! x0 on ARM64 (saved to `zbi_paddr` by start.S) and a1 on RISC-V (saved
+//! to `boot_dtb_paddr`). This module normalizes the blob into a
+//! `KernelHandoff`.
+
+use crate::kernel::dev::fdt::platform::MAX_RESERVED_RANGES;
+use crate::kernel::dev::fdt::{Fdt, PlatformInfo};
+use crate::kernel::handoff::{KernelHandoff, MemoryType};
+
+/// DTB address saved by the RISC-V entry code (a1 at entry)
+///
+/// Lives in .data so clearing .bss does not zero it.
+#[cfg(target_arch = "riscv64")]
+#[export_name = "boot_dtb_paddr"]
+#[link_section = ".data"]
+static BOOT_DTB_PADDR: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+/// Physical address of the DTB passed by firmware, if any
+pub fn boot_dtb_paddr() -> Option<u64> {
+    #[cfg(target_arch = "aarch64")]
+    let paddr = {
+        extern "C" {
+            static zbi_paddr: u64;
+        }
+        unsafe { core::ptr::read_volatile(core::ptr::addr_of!(zbi_paddr)) }
+    };
+
+    #[cfg(target_arch = "riscv64")]
+    let paddr = BOOT_DTB_PADDR.load(core::sync::atomic::Ordering::Relaxed);
+
+    #[cfg(not(any(target_arch = "aarch64", target_arch = "riscv64")))]
+    let paddr = 0u64;
+
+    // start.S initializes zbi_paddr to -1
+    if paddr == 0 || paddr == u64::MAX {
+        None
+    } else {
+        Some(paddr)
+    }
+}
+
+/// Normalize the device tree at `paddr` into `handoff`
+///
+/// Returns false if no valid blob is present.
+///
+/// # Safety
+///
+/// `paddr` must be identity mapped and readable.
+pub unsafe fn normalize(paddr: u64, handoff: &mut KernelHandoff) -> bool {
+    let fdt = match Fdt::from_phys(paddr) {
+        Ok(fdt) => fdt,
+        Err(_) => return false,
+    };
+    fill(&fdt, paddr, handoff);
+    true
+}
+
+/// Fill `handoff` from a parsed tree located at `paddr`
+fn fill(fdt: &Fdt, paddr: u64, handoff: &mut KernelHandoff) {
+    let info = PlatformInfo::from_fdt(fdt);
+
+    // The blob itself stays reserved until the kernel is done with it
+    let mut reserved = [(0u64, 0u64); MAX_RESERVED_RANGES + 1];
+    let count = info.reserved().len();
+    reserved[..count].copy_from_slice(info.reserved());
+    let dtb_base = paddr & !0xfff;
+    let dtb_end = (paddr + fdt.size() as u64 + 0xfff) & !0xfff;
+    reserved[count] = (dtb_base, dtb_end - dtb_base);
+    let reserved = &reserved[..count + 1];
+
+    for &(base, length) in info.memory() {
+        push_available(handoff, base, length, reserved);
+    }
+    for &(base, length) in reserved {
+        if info.ramoops != Some((base, length)) {
+            handoff.push_memory_range(base, length, MemoryType::Reserved);
+        }
+    }
+    if let Some((base, length)) = info.ramoops {
+        handoff.push_memory_range(base, length, MemoryType::Persistent);
+    }
+
+    if let Some(chosen) = fdt.find_node("/chosen") {
+        if let Some(bootargs) = chosen.property("bootargs") {
+            let bytes = bootargs.value;
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            handoff.set_cmdline(&bytes[..len]);
+        }
+    }
+
+    if let Some((start, end)) = info.initrd {
+        handoff.push_module(start, end - start, b"initrd");
+    }
+
+    handoff.devicetree = paddr;
+}
+
+/// Add `[base, base + length)` as available memory, minus any overlap
+/// with `reserved`
+fn push_available(handoff: &mut KernelHandoff, base: u64, length: u64, reserved: &[(u64, u64)]) {
+    if length == 0 {
+        return;
+    }
+    let end = base.saturating_add(length);
+
+    let overlap = reserved.iter().find(|&&(rbase, rlen)| {
+        rlen != 0 && rbase < end && rbase.saturating_add(rlen) > base
+    });
+
+    match overlap {
+        None => {
+            handoff.push_memory_range(base, length, MemoryType::Available);
+        }
+        Some(&(rbase, rlen)) => {
+            let rend = rbase.saturating_add(rlen);
+            if rbase > base {
+                push_available(handoff, base, rbase - base, reserved);
+            }
+            if rend < end {
+                push_available(handoff, rend, end - rend, reserved);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::fdt::tests::DtbBuilder;
+
+    #[test]
+    fn test_fill_from_dtb() {
+        let mut b = DtbBuilder::new();
+        b.begin("")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .begin("chosen")
+            .prop("bootargs", b"console=ttyS0\0")
+            .prop_cells("linux,initrd-start", &[0x4400_0000])
+            .prop_cells("linux,initrd-end", &[0x4410_0000])
+            .end()
+            .begin("memory@40000000")
+            .prop("device_type", b"memory\0")
+            .prop_cells("reg", &[0, 0x4000_0000, 0, 0x1000_0000])
+            .end()
+            .begin("reserved-memory")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .begin("ramoops@4f000000")
+            .prop("compatible", b"ramoops\0")
+            .prop_cells("reg", &[0, 0x4f00_0000, 0, 0x1_0000])
+            .end()
+            .end()
+            .end();
+        let mut buf = [0u8; 4096];
+        let len = b.finish(&mut buf);
+        let fdt = Fdt::parse(&buf[..len]).unwrap();
+
+        let mut handoff = KernelHandoff::empty();
+        fill(&fdt, 0x5000_0000, &mut handoff);
+
+        assert_eq!(handoff.devicetree(), Some(0x5000_0000));
+        assert_eq!(handoff.cmdline(), "console=ttyS0");
+        assert_eq!(handoff.modules().len(), 1);
+        assert_eq!(handoff.modules()[0].length, 0x10_0000);
+
+        // The builder's reservation at 0x4800_0000 and the ramoops region
+        // split the RAM range
+        let available: u64 = handoff
+            .memory_ranges()
+            .iter()
+            .filter(|r| r.mem_type == MemoryType::Available)
+            .map(|r| r.length)
+            .sum();
+        assert_eq!(available, 0x1000_0000 - 0x1000 - 0x1_0000);
+
+        let persistent = handoff
+            .memory_ranges()
+            .iter()
+            .find(|r| r.mem_type == MemoryType::Persistent)
+            .unwrap();
+        assert_eq!((persistent.base, persistent.length), (0x4f00_0000, 0x1_0000));
+    }
+
+    #[test]
+    fn test_push_available_carves_reserved() {
+        let mut handoff = KernelHandoff::empty();
+        push_available(&mut handoff, 0x1000, 0x9000, &[(0x3000, 0x1000), (0x6000, 0x1000)]);
+
+        let ranges = handoff.memory_ranges();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!((ranges[0].base, ranges[0].length), (0x1000, 0x2000));
+        assert_eq!((ranges[1].base, ranges[1].length), (0x4000, 0x2000));
+        assert_eq!((ranges[2].base, ranges[2].length), (0x7000, 0x3000));
+    }
+}
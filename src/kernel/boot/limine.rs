@@ -0,0 +1,344 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Limine Boot Protocol
+//!
+//! Limine scans the kernel image for request structures, fills in their
+//! `response` pointers and enters the kernel ELF entry point in long mode
+//! with all general purpose registers zeroed, so `kmain` sees a null
+//! handoff pointer. `normalize` then converts the responses into a
+//! `KernelHandoff`.
+//!
+//! # Notes
+//!
+//! Response pointers and the addresses inside them are virtual addresses
+//! in the higher half direct map (HHDM). They are converted back to
+//! physical addresses using the HHDM response so the handoff stays
+//! protocol independent.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use super::c_str_bytes;
+use crate::kernel::handoff::{
+    FramebufferFormat, KernelHandoff, MemoryType, HANDOFF_CMDLINE_MAX, HANDOFF_MODULE_NAME_MAX,
+};
+
+/// ============================================================================
+/// Request ABI
+/// ============================================================================
+
+/// Common magic shared by all request identifiers
+const COMMON_MAGIC: [u64; 2] = [0xc7b1_dd30_df4c_8b88, 0x0a82_e883_a194_f07b];
+
+/// Memory map entry types
+const MEMMAP_USABLE: u64 = 0;
+const MEMMAP_ACPI_RECLAIMABLE: u64 = 2;
+const MEMMAP_BOOTLOADER_RECLAIMABLE: u64 = 5;
+const MEMMAP_KERNEL_AND_MODULES: u64 = 6;
+const MEMMAP_FRAMEBUFFER: u64 = 7;
+
+/// Framebuffer memory model: RGB
+const FRAMEBUFFER_RGB: u8 = 1;
+
+/// A Limine request
+///
+/// The bootloader writes `response` before entering the kernel.
+#[repr(C)]
+pub struct Request<R> {
+    id: [u64; 4],
+    revision: u64,
+    response: AtomicPtr<R>,
+}
+
+impl<R> Request<R> {
+    const fn new(id: [u64; 2]) -> Self {
+        Self {
+            id: [COMMON_MAGIC[0], COMMON_MAGIC[1], id[0], id[1]],
+            revision: 0,
+            response: AtomicPtr::new(core::ptr::null_mut()),
+        }
+    }
+
+    /// Get the response, if the bootloader answered this request
+    fn response(&self) -> Option<&'static R> {
+        let ptr = self.response.load(Ordering::Acquire);
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { &*ptr })
+        }
+    }
+}
+
+#[repr(C)]
+pub struct HhdmResponse {
+    revision: u64,
+    offset: u64,
+}
+
+#[repr(C)]
+pub struct MemmapEntry {
+    base: u64,
+    length: u64,
+    entry_type: u64,
+}
+
+#[repr(C)]
+pub struct MemmapResponse {
+    revision: u64,
+    entry_count: u64,
+    entries: *const *const MemmapEntry,
+}
+
+#[repr(C)]
+pub struct Framebuffer {
+    address: u64,
+    width: u64,
+    height: u64,
+    pitch: u64,
+    bpp: u16,
+    memory_model: u8,
+    red_mask_size: u8,
+    red_mask_shift: u8,
+    green_mask_size: u8,
+    green_mask_shift: u8,
+    blue_mask_size: u8,
+    blue_mask_shift: u8,
+}
+
+#[repr(C)]
+pub struct FramebufferResponse {
+    revision: u64,
+    framebuffer_count: u64,
+    framebuffers: *const *const Framebuffer,
+}
+
+#[repr(C)]
+pub struct RsdpResponse {
+    revision: u64,
+    address: u64,
+}
+
+#[repr(C)]
+pub struct SmbiosResponse {
+    revision: u64,
+    entry_32: u64,
+    entry_64: u64,
+}
+
+#[repr(C)]
+pub struct File {
+    revision: u64,
+    address: u64,
+    size: u64,
+    path: u64,
+    cmdline: u64,
+}
+
+#[repr(C)]
+pub struct ModuleResponse {
+    revision: u64,
+    module_count: u64,
+    modules: *const *const File,
+}
+
+#[repr(C)]
+pub struct KernelFileResponse {
+    revision: u64,
+    kernel_file: *const File,
+}
+
+/// ============================================================================
+/// Requests
+/// ============================================================================
+
+/// Start marker for the request area
+#[used]
+#[link_section = ".limine_requests_start"]
+static REQUESTS_START: [u64; 4] = [
+    0xf6b8_f4b3_9de7_d1ae, 0xfab9_1a69_40fc_b9cf,
+    0x785c_6ed0_15d3_e316, 0x181e_920a_7852_b9d9,
+];
+
+#[used]
+#[link_section = ".limine_requests"]
+static HHDM_REQUEST: Request<HhdmResponse> =
+    Request::new([0x48dc_f1cb_8ad2_b852, 0x6398_4e95_9a98_244b]);
+
+#[used]
+#[link_section = ".limine_requests"]
+static MEMMAP_REQUEST: Request<MemmapResponse> =
+    Request::new([0x67cf_3d9d_378a_806f, 0xe304_acdf_c50c_3c62]);
+
+#[used]
+#[link_section = ".limine_requests"]
+static FRAMEBUFFER_REQUEST: Request<FramebufferResponse> =
+    Request::new([0x9d58_27dc_d881_dd75, 0xa314_8604_f6fa_b11b]);
+
+#[used]
+#[link_section = ".limine_requests"]
+static RSDP_REQUEST: Request<RsdpResponse> =
+    Request::new([0xc5e7_7b6b_397e_7b43, 0x2763_7845_accd_cf3c]);
+
+#[used]
+#[link_section = ".limine_requests"]
+static SMBIOS_REQUEST: Request<SmbiosResponse> =
+    Request::new([0x9e90_46f1_1e09_5391, 0xaa4a_520f_efbd_e5ee]);
+
+#[used]
+#[link_section = ".limine_requests"]
+static MODULE_REQUEST: Request<ModuleResponse> =
+    Request::new([0x3e7e_2797_02be_32af, 0xca1c_4f3b_d128_0cee]);
+
+#[used]
+#[link_section = ".limine_requests"]
+static KERNEL_FILE_REQUEST: Request<KernelFileResponse> =
+    Request::new([0xad97_e90e_83f1_ed67, 0x31eb_5d1c_5ff2_3b69]);
+
+/// End marker for the request area
+#[used]
+#[link_section = ".limine_requests_end"]
+static REQUESTS_END: [u64; 2] = [0xadc0_e053_1bb1_0d03, 0x9572_709f_3176_4c62];
+
+/// ============================================================================
+/// Normalization
+/// ============================================================================
+
+/// Map a Limine memory map type to a handoff memory type
+///
+/// Bootloader-reclaimable memory holds the responses themselves, so it is
+/// reported as reclaimable rather than available.
+fn memmap_type(entry_type: u64) -> MemoryType {
+    match entry_type {
+        MEMMAP_USABLE => MemoryType::Available,
+        MEMMAP_ACPI_RECLAIMABLE | MEMMAP_BOOTLOADER_RECLAIMABLE => MemoryType::Reclaimable,
+        MEMMAP_FRAMEBUFFER => MemoryType::Peripheral,
+        MEMMAP_KERNEL_AND_MODULES => MemoryType::Reserved,
+        _ => MemoryType::Reserved,
+    }
+}
+
+/// Convert an HHDM virtual address to physical
+fn to_phys(addr: u64, hhdm: u64) -> u64 {
+    if hhdm != 0 && addr >= hhdm {
+        addr - hhdm
+    } else {
+        addr
+    }
+}
+
+/// Normalize the Limine responses into `handoff`
+///
+/// Returns false if the kernel was not booted by Limine (no memory map
+/// response).
+///
+/// # Safety
+///
+/// Must be called before the bootloader-reclaimable memory is reused.
+pub unsafe fn normalize(handoff: &mut KernelHandoff) -> bool {
+    let memmap = match MEMMAP_REQUEST.response() {
+        Some(m) => m,
+        None => return false,
+    };
+
+    let hhdm = HHDM_REQUEST.response().map(|h| h.offset).unwrap_or(0);
+
+    let entries = core::slice::from_raw_parts(memmap.entries, memmap.entry_count as usize);
+    for &entry in entries {
+        let entry = &*entry;
+        if !handoff.push_memory_range(entry.base, entry.length, memmap_type(entry.entry_type)) {
+            break;
+        }
+    }
+
+    if let Some(rsdp) = RSDP_REQUEST.response() {
+        handoff.acpi_rsdp = to_phys(rsdp.address, hhdm);
+    }
+
+    if let Some(smbios) = SMBIOS_REQUEST.response() {
+        let entry = if smbios.entry_64 != 0 { smbios.entry_64 } else { smbios.entry_32 };
+        handoff.smbios_entry = to_phys(entry, hhdm);
+    }
+
+    if let Some(fbs) = FRAMEBUFFER_REQUEST.response() {
+        if fbs.framebuffer_count > 0 {
+            fill_framebuffer(&**fbs.framebuffers, hhdm, handoff);
+        }
+    }
+
+    if let Some(kernel) = KERNEL_FILE_REQUEST.response() {
+        if !kernel.kernel_file.is_null() {
+            let file = &*kernel.kernel_file;
+            handoff.set_cmdline(c_str_bytes(file.cmdline, HANDOFF_CMDLINE_MAX));
+        }
+    }
+
+    if let Some(modules) = MODULE_REQUEST.response() {
+        let files = core::slice::from_raw_parts(modules.modules, modules.module_count as usize);
+        for &file in files {
+            let file = &*file;
+            let name = c_str_bytes(file.path, HANDOFF_MODULE_NAME_MAX);
+            if !handoff.push_module(to_phys(file.address, hhdm), file.size, name) {
+                break;
+            }
+        }
+    }
+
+    true
+}
+
+fn fill_framebuffer(fb: &Framebuffer, hhdm: u64, handoff: &mut KernelHandoff) {
+    if fb.memory_model != FRAMEBUFFER_RGB || fb.bpp != 32 {
+        return;
+    }
+
+    let format = match (fb.red_mask_shift, fb.blue_mask_shift) {
+        (0, 16) => FramebufferFormat::RGB,
+        (16, 0) => FramebufferFormat::BGR,
+        _ => return,
+    };
+
+    handoff.framebuffer.base = to_phys(fb.address, hhdm);
+    handoff.framebuffer.size = fb.pitch * fb.height;
+    handoff.framebuffer.width = fb.width as u32;
+    handoff.framebuffer.height = fb.height as u32;
+    handoff.framebuffer.stride = (fb.pitch / 4) as u32;
+    handoff.framebuffer.format = format;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_layout() {
+        assert_eq!(core::mem::size_of::<Request<MemmapResponse>>(), 48);
+        assert_eq!(MEMMAP_REQUEST.id[0], COMMON_MAGIC[0]);
+        assert_eq!(MEMMAP_REQUEST.id[3], 0xe304_acdf_c50c_3c62);
+    }
+
+    #[test]
+    fn test_memmap_type() {
+        assert_eq!(memmap_type(MEMMAP_USABLE), MemoryType::Available);
+        assert_eq!(memmap_type(MEMMAP_BOOTLOADER_RECLAIMABLE), MemoryType::Reclaimable);
+        assert_eq!(memmap_type(MEMMAP_FRAMEBUFFER), MemoryType::Peripheral);
+        assert_eq!(memmap_type(99), MemoryType::Reserved);
+    }
+
+    #[test]
+    fn test_to_phys() {
+        let hhdm = 0xffff_8000_0000_0000;
+        assert_eq!(to_phys(hhdm + 0x1000, hhdm), 0x1000);
+        assert_eq!(to_phys(0x1000, hhdm), 0x1000);
+        assert_eq!(to_phys(0x1000, 0), 0x1000);
+    }
+
+    #[test]
+    fn test_unanswered_request() {
+        let mut h = KernelHandoff::empty();
+        assert!(!unsafe { normalize(&mut h) });
+    }
+}
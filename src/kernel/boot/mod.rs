@@ -0,0 +1,259 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot Protocol Abstraction
+//!
+//! The kernel can be started by several boot protocols:
+//! - UEFI via `uefi-loader`, which builds a `KernelHandoff` itself
+//! - Multiboot2 (GRUB), entered through `boot_entry32`
+//! - PVH (QEMU `-kernel`), entered through `boot_entry32`
+//! - Limine, which enters `kmain` directly in long mode and answers the
+//!   request structures in `limine.rs`
+//! - Device tree (ARM64 / RISC-V firmware), which passes a DTB address
+//!   in a register
+//!
+//! # Design
+//!
+//! Every non-UEFI protocol is normalized into a statically allocated
+//! `KernelHandoff` so the rest of the kernel only consumes one format.
+//! Normalization runs before any other kernel subsystem, so the parsers
+//! must not allocate, log or take locks.
+//!
+//! # Usage
+//!
+//! ```rust
+//! // In kmain, before kernel init
+//! let handoff = unsafe { boot::resolve_handoff(handoff) };
+//! unsafe { handoff::set(handoff) };
+//! ```
+
+// Protocol structures mirror the full ABI layout, not every field is read
+#[allow(dead_code)]
+pub mod limine;
+pub mod devicetree;
+pub mod multiboot2;
+pub mod pvh;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::kernel::handoff::KernelHandoff;
+
+/// ============================================================================
+/// Boot Protocol
+/// ============================================================================
+
+/// Boot protocol the kernel was started with
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootProtocol {
+    /// Unknown / no boot information
+    None = 0,
+
+    /// UEFI loader handoff
+    Uefi = 1,
+
+    /// Multiboot2 boot information
+    Multiboot2 = 2,
+
+    /// Limine boot protocol
+    Limine = 3,
+
+    /// PVH start info
+    Pvh = 4,
+
+    /// Flattened device tree from firmware
+    DeviceTree = 5,
+}
+
+impl BootProtocol {
+    /// Convert from raw value
+    pub const fn from_raw(raw: u32) -> Self {
+        match raw {
+            1 => Self::Uefi,
+            2 => Self::Multiboot2,
+            3 => Self::Limine,
+            4 => Self::Pvh,
+            5 => Self::DeviceTree,
+            _ => Self::None,
+        }
+    }
+
+    /// Human readable protocol name
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Uefi => "uefi",
+            Self::Multiboot2 => "multiboot2",
+            Self::Limine => "limine",
+            Self::Pvh => "pvh",
+            Self::DeviceTree => "devicetree",
+        }
+    }
+}
+
+/// Detected boot protocol
+static PROTOCOL: AtomicU32 = AtomicU32::new(BootProtocol::None as u32);
+
+/// Handoff block filled in for non-UEFI protocols
+static mut NORMALIZED: KernelHandoff = KernelHandoff::empty();
+
+/// Get the protocol the kernel was booted with
+pub fn protocol() -> BootProtocol {
+    BootProtocol::from_raw(PROTOCOL.load(Ordering::Acquire))
+}
+
+fn set_protocol(protocol: BootProtocol) {
+    PROTOCOL.store(protocol as u32, Ordering::Release);
+}
+
+/// Get the normalized handoff block for filling in
+///
+/// # Safety
+///
+/// Must only be called on the boot CPU before `handoff::set`.
+unsafe fn normalized() -> &'static mut KernelHandoff {
+    &mut *core::ptr::addr_of_mut!(NORMALIZED)
+}
+
+/// Record the kernel image extent from linker symbols
+#[cfg(not(test))]
+fn fill_kernel_image(handoff: &mut KernelHandoff) {
+    extern "C" {
+        static __kernel_start: u8;
+        static __kernel_end: u8;
+    }
+
+    let start = unsafe { core::ptr::addr_of!(__kernel_start) } as u64;
+    let end = unsafe { core::ptr::addr_of!(__kernel_end) } as u64;
+    if end > start {
+        handoff.kernel_base = start;
+        handoff.kernel_size = end - start;
+    }
+}
+
+/// Host test builds are not linked with the kernel linker script
+#[cfg(test)]
+fn fill_kernel_image(_handoff: &mut KernelHandoff) {}
+
+/// ============================================================================
+/// Entry Points
+/// ============================================================================
+
+/// Resolve the handoff pointer passed to `kmain`
+///
+/// A valid UEFI handoff is returned unchanged. Otherwise the Limine
+/// responses and then a firmware-provided device tree are checked and,
+/// if present, normalized. Returns null if no boot information could be
+/// found.
+///
+/// # Safety
+///
+/// Must be called once on the boot CPU before `handoff::set`.
+pub unsafe fn resolve_handoff(ptr: *const KernelHandoff) -> *const KernelHandoff {
+    if !ptr.is_null() && (*ptr).is_valid() {
+        set_protocol(BootProtocol::Uefi);
+        return ptr;
+    }
+
+    let handoff = normalized();
+    if limine::normalize(handoff) {
+        fill_kernel_image(handoff);
+        set_protocol(BootProtocol::Limine);
+        return handoff;
+    }
+
+    if let Some(dtb) = devicetree::boot_dtb_paddr() {
+        if devicetree::normalize(dtb, handoff) {
+            fill_kernel_image(handoff);
+            set_protocol(BootProtocol::DeviceTree);
+            return handoff;
+        }
+    }
+
+    core::ptr::null()
+}
+
+/// Long mode continuation of the 32-bit Multiboot2 / PVH entry
+///
+/// Called by `boot_entry32` (multiboot_entry.S) with the values of eax and
+/// ebx at entry. The first 4 GiB are identity mapped.
+#[cfg(target_arch = "x86_64")]
+#[no_mangle]
+pub unsafe extern "C" fn boot_entry64(magic: u32, info: u32) -> ! {
+    let handoff = normalized();
+
+    let protocol = if magic == multiboot2::BOOTLOADER_MAGIC
+        && multiboot2::normalize(info as u64, handoff)
+    {
+        BootProtocol::Multiboot2
+    } else if pvh::normalize(info as u64, handoff) {
+        BootProtocol::Pvh
+    } else {
+        BootProtocol::None
+    };
+
+    let ptr = if protocol != BootProtocol::None {
+        fill_kernel_image(handoff);
+        handoff as *const KernelHandoff
+    } else {
+        core::ptr::null()
+    };
+    set_protocol(protocol);
+
+    crate::kmain(ptr)
+}
+
+/// ============================================================================
+/// Helpers
+/// ============================================================================
+
+/// Length of a NUL-terminated string at a physical address, bounded
+///
+/// # Safety
+///
+/// `addr` must be identity mapped and readable up to the terminator.
+pub(crate) unsafe fn c_str_bytes(addr: u64, max: usize) -> &'static [u8] {
+    if addr == 0 {
+        return &[];
+    }
+
+    let ptr = addr as *const u8;
+    let mut len = 0;
+    while len < max && *ptr.add(len) != 0 {
+        len += 1;
+    }
+    core::slice::from_raw_parts(ptr, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boot_protocol_roundtrip() {
+        for p in [
+            BootProtocol::None,
+            BootProtocol::Uefi,
+            BootProtocol::Multiboot2,
+            BootProtocol::Limine,
+            BootProtocol::Pvh,
+            BootProtocol::DeviceTree,
+        ] {
+            assert_eq!(BootProtocol::from_raw(p as u32), p);
+        }
+        assert_eq!(BootProtocol::from_raw(99), BootProtocol::None);
+    }
+
+    #[test]
+    fn test_c_str_bytes() {
+        let s = b"root=/dev/sda\0ignored";
+        let bytes = unsafe { c_str_bytes(s.as_ptr() as u64, 64) };
+        assert_eq!(bytes, b"root=/dev/sda");
+
+        let bounded = unsafe { c_str_bytes(s.as_ptr() as u64, 4) };
+        assert_eq!(bounded, b"root");
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Multiboot2 Boot Information
+//!
+//! Parses the Multiboot2 boot information structure passed in ebx by GRUB
+//! and other Multiboot2 loaders and normalizes it into a `KernelHandoff`.
+//!
+//! # Layout
+//!
+//! The structure starts with `total_size: u32, reserved: u32` followed by
+//! 8-byte aligned tags, each with a `type: u32, size: u32` header. The
+//! list ends with a tag of type 0.
+
+use crate::kernel::handoff::{FramebufferFormat, KernelHandoff, MemoryType};
+
+/// Value of eax when entered by a Multiboot2 loader
+pub const BOOTLOADER_MAGIC: u32 = 0x36d7_6289;
+
+/// Upper bound on the boot information size we accept
+const MAX_INFO_SIZE: usize = 64 * 1024;
+
+/// Tag types
+const TAG_END: u32 = 0;
+const TAG_CMDLINE: u32 = 1;
+const TAG_MODULE: u32 = 3;
+const TAG_MMAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_SMBIOS: u32 = 13;
+const TAG_ACPI_OLD: u32 = 14;
+const TAG_ACPI_NEW: u32 = 15;
+
+/// Memory map entry types
+const MMAP_AVAILABLE: u32 = 1;
+const MMAP_ACPI_RECLAIMABLE: u32 = 3;
+
+/// Framebuffer type: direct RGB
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+
+fn read_u8(buf: &[u8], off: usize) -> u8 {
+    buf.get(off).copied().unwrap_or(0)
+}
+
+fn read_u32(buf: &[u8], off: usize) -> u32 {
+    match buf.get(off..off + 4) {
+        Some(b) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        None => 0,
+    }
+}
+
+fn read_u64(buf: &[u8], off: usize) -> u64 {
+    read_u32(buf, off) as u64 | (read_u32(buf, off + 4) as u64) << 32
+}
+
+/// Normalize the boot information at physical address `info`
+///
+/// # Safety
+///
+/// `info` must be the ebx value passed by a Multiboot2 loader and the
+/// structure must be identity mapped.
+pub unsafe fn normalize(info: u64, handoff: &mut KernelHandoff) -> bool {
+    if info == 0 || info & 7 != 0 {
+        return false;
+    }
+
+    let total = *(info as *const u32) as usize;
+    if !(16..=MAX_INFO_SIZE).contains(&total) {
+        return false;
+    }
+
+    let bytes = core::slice::from_raw_parts(info as *const u8, total);
+    parse(bytes, info, handoff)
+}
+
+/// Parse a Multiboot2 boot information block
+///
+/// `phys` is the physical address of `info[0]`, used to report the
+/// addresses of tables embedded in tags (RSDP, SMBIOS).
+pub fn parse(info: &[u8], phys: u64, handoff: &mut KernelHandoff) -> bool {
+    let total = read_u32(info, 0) as usize;
+    if total < 16 || total > info.len() {
+        return false;
+    }
+    let info = &info[..total];
+
+    let mut have_acpi_new = false;
+    let mut offset = 8;
+
+    while offset + 8 <= total {
+        let tag_type = read_u32(info, offset);
+        let tag_size = read_u32(info, offset + 4) as usize;
+
+        if tag_type == TAG_END {
+            return true;
+        }
+        if tag_size < 8 || offset + tag_size > total {
+            return false;
+        }
+
+        let tag = &info[offset..offset + tag_size];
+        match tag_type {
+            TAG_CMDLINE => handoff.set_cmdline(&tag[8..]),
+            TAG_MODULE => {
+                let start = read_u32(tag, 8) as u64;
+                let end = read_u32(tag, 12) as u64;
+                if end > start {
+                    handoff.push_module(start, end - start, tag.get(16..).unwrap_or(&[]));
+                }
+            }
+            TAG_MMAP => parse_mmap(tag, handoff),
+            TAG_FRAMEBUFFER => parse_framebuffer(tag, handoff),
+            TAG_SMBIOS => {
+                if tag_size > 16 {
+                    handoff.smbios_entry = phys + offset as u64 + 16;
+                }
+            }
+            TAG_ACPI_NEW => {
+                handoff.acpi_rsdp = phys + offset as u64 + 8;
+                have_acpi_new = true;
+            }
+            TAG_ACPI_OLD => {
+                if !have_acpi_new {
+                    handoff.acpi_rsdp = phys + offset as u64 + 8;
+                }
+            }
+            _ => {}
+        }
+
+        offset += (tag_size + 7) & !7;
+    }
+
+    // Missing end tag
+    false
+}
+
+fn parse_mmap(tag: &[u8], handoff: &mut KernelHandoff) {
+    let entry_size = read_u32(tag, 8) as usize;
+    if entry_size < 24 {
+        return;
+    }
+
+    let mut off = 16;
+    while off + entry_size <= tag.len() {
+        let base = read_u64(tag, off);
+        let length = read_u64(tag, off + 8);
+        let mem_type = match read_u32(tag, off + 16) {
+            MMAP_AVAILABLE => MemoryType::Available,
+            MMAP_ACPI_RECLAIMABLE => MemoryType::Reclaimable,
+            _ => MemoryType::Reserved,
+        };
+
+        if !handoff.push_memory_range(base, length, mem_type) {
+            return;
+        }
+        off += entry_size;
+    }
+}
+
+fn parse_framebuffer(tag: &[u8], handoff: &mut KernelHandoff) {
+    let base = read_u64(tag, 8);
+    let pitch = read_u32(tag, 16);
+    let width = read_u32(tag, 20);
+    let height = read_u32(tag, 24);
+    let bpp = read_u8(tag, 28);
+    let fb_type = read_u8(tag, 29);
+
+    if fb_type != FRAMEBUFFER_TYPE_RGB || bpp != 32 || base == 0 {
+        return;
+    }
+
+    let red_pos = read_u8(tag, 32);
+    let blue_pos = read_u8(tag, 36);
+    let format = match (red_pos, blue_pos) {
+        (0, 16) => FramebufferFormat::RGB,
+        (16, 0) => FramebufferFormat::BGR,
+        _ => return,
+    };
+
+    handoff.framebuffer.base = base;
+    handoff.framebuffer.size = pitch as u64 * height as u64;
+    handoff.framebuffer.width = width;
+    handoff.framebuffer.height = height;
+    handoff.framebuffer.stride = pitch / 4;
+    handoff.framebuffer.format = format;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal boot information builder
+    struct InfoBuilder {
+        buf: [u8; 512],
+        len: usize,
+    }
+
+    impl InfoBuilder {
+        fn new() -> Self {
+            Self { buf: [0; 512], len: 8 }
+        }
+
+        fn put(&mut self, bytes: &[u8]) {
+            self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        }
+
+        fn tag(&mut self, tag_type: u32, body: &[u8]) {
+            self.put(&tag_type.to_le_bytes());
+            self.put(&((8 + body.len()) as u32).to_le_bytes());
+            self.put(body);
+            self.len = (self.len + 7) & !7;
+        }
+
+        fn finish(mut self) -> ([u8; 512], usize) {
+            self.tag(TAG_END, &[]);
+            let len = self.len;
+            self.buf[0..4].copy_from_slice(&(len as u32).to_le_bytes());
+            (self.buf, len)
+        }
+    }
+
+    #[test]
+    fn test_parse_mmap_cmdline_module() {
+        let mut b = InfoBuilder::new();
+        b.tag(TAG_CMDLINE, b"kernel.fbcon=false\0");
+
+        let mut module = [0u8; 20];
+        module[0..4].copy_from_slice(&0x20_0000u32.to_le_bytes());
+        module[4..8].copy_from_slice(&0x20_1000u32.to_le_bytes());
+        module[8..20].copy_from_slice(b"bootfs.img\0\0");
+        b.tag(TAG_MODULE, &module);
+
+        let mut mmap = [0u8; 8 + 24 * 2];
+        mmap[0..4].copy_from_slice(&24u32.to_le_bytes());
+        mmap[8..16].copy_from_slice(&0u64.to_le_bytes());
+        mmap[16..24].copy_from_slice(&0x9_f000u64.to_le_bytes());
+        mmap[24..28].copy_from_slice(&MMAP_AVAILABLE.to_le_bytes());
+        mmap[32..40].copy_from_slice(&0x10_0000u64.to_le_bytes());
+        mmap[40..48].copy_from_slice(&0x100_0000u64.to_le_bytes());
+        mmap[48..52].copy_from_slice(&MMAP_ACPI_RECLAIMABLE.to_le_bytes());
+        b.tag(TAG_MMAP, &mmap);
+
+        let (buf, len) = b.finish();
+        let mut h = KernelHandoff::empty();
+        assert!(parse(&buf[..len], 0x1000, &mut h));
+
+        assert_eq!(h.cmdline(), "kernel.fbcon=false");
+        assert_eq!(h.modules().len(), 1);
+        assert_eq!(h.modules()[0].length, 0x1000);
+        assert_eq!(h.modules()[0].name(), "bootfs.img");
+
+        let ranges = h.memory_ranges();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].mem_type, MemoryType::Available);
+        assert_eq!(ranges[1].mem_type, MemoryType::Reclaimable);
+    }
+
+    #[test]
+    fn test_parse_rsdp_prefers_acpi2() {
+        let mut b = InfoBuilder::new();
+        b.tag(TAG_ACPI_NEW, &[0u8; 36]);
+        let new_offset = 8;
+        b.tag(TAG_ACPI_OLD, &[0u8; 20]);
+
+        let (buf, len) = b.finish();
+        let mut h = KernelHandoff::empty();
+        assert!(parse(&buf[..len], 0x1000, &mut h));
+        assert_eq!(h.acpi_rsdp, 0x1000 + new_offset + 8);
+    }
+
+    #[test]
+    fn test_parse_framebuffer_bgr() {
+        let mut fb = [0u8; 32];
+        fb[0..8].copy_from_slice(&0xfd00_0000u64.to_le_bytes());
+        fb[8..12].copy_from_slice(&(1024u32 * 4).to_le_bytes());
+        fb[12..16].copy_from_slice(&1024u32.to_le_bytes());
+        fb[16..20].copy_from_slice(&768u32.to_le_bytes());
+        fb[20] = 32;
+        fb[21] = FRAMEBUFFER_TYPE_RGB;
+        fb[24] = 16; // red position
+        fb[25] = 8;
+        fb[26] = 8; // green position
+        fb[27] = 8;
+        fb[28] = 0; // blue position
+        fb[29] = 8;
+
+        let mut b = InfoBuilder::new();
+        b.tag(TAG_FRAMEBUFFER, &fb);
+        let (buf, len) = b.finish();
+
+        let mut h = KernelHandoff::empty();
+        assert!(parse(&buf[..len], 0, &mut h));
+        let info = h.framebuffer().unwrap();
+        assert_eq!(info.format, FramebufferFormat::BGR);
+        assert_eq!(info.stride, 1024);
+        assert_eq!(info.height, 768);
+    }
+
+    #[test]
+    fn test_parse_rejects_truncated() {
+        let mut buf = [0u8; 16];
+        buf[0..4].copy_from_slice(&64u32.to_le_bytes());
+        let mut h = KernelHandoff::empty();
+        assert!(!parse(&buf, 0, &mut h));
+    }
+}
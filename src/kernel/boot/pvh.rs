@@ -0,0 +1,168 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PVH Start Info
+//!
+//! QEMU `-kernel` (and Xen) start ELF kernels carrying a
+//! `XEN_ELFNOTE_PHYS32_ENTRY` note in 32-bit mode with ebx pointing at an
+//! `hvm_start_info` structure. This module normalizes it into a
+//! `KernelHandoff`.
+
+use super::c_str_bytes;
+use crate::kernel::handoff::{KernelHandoff, MemoryType, HANDOFF_CMDLINE_MAX, HANDOFF_MODULE_NAME_MAX};
+
+/// hvm_start_info magic ("xEn3" with the high bit set)
+pub const START_INFO_MAGIC: u32 = 0x336e_c578;
+
+/// E820 memory types used by the PVH memory map
+const E820_RAM: u32 = 1;
+const E820_ACPI: u32 = 3;
+
+/// hvm_start_info (version 1)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StartInfo {
+    pub magic: u32,
+    pub version: u32,
+    pub flags: u32,
+    pub nr_modules: u32,
+    pub modlist_paddr: u64,
+    pub cmdline_paddr: u64,
+    pub rsdp_paddr: u64,
+    pub memmap_paddr: u64,
+    pub memmap_entries: u32,
+    pub reserved: u32,
+}
+
+/// hvm_modlist_entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ModlistEntry {
+    pub paddr: u64,
+    pub size: u64,
+    pub cmdline_paddr: u64,
+    pub reserved: u64,
+}
+
+/// hvm_memmap_table_entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MemmapEntry {
+    pub addr: u64,
+    pub size: u64,
+    pub mem_type: u32,
+    pub reserved: u32,
+}
+
+/// Map an E820 type to a handoff memory type
+fn e820_type(mem_type: u32) -> MemoryType {
+    match mem_type {
+        E820_RAM => MemoryType::Available,
+        E820_ACPI => MemoryType::Reclaimable,
+        _ => MemoryType::Reserved,
+    }
+}
+
+/// Normalize the start info at physical address `info`
+///
+/// # Safety
+///
+/// `info` and every address it references must be identity mapped.
+pub unsafe fn normalize(info: u64, handoff: &mut KernelHandoff) -> bool {
+    if info == 0 || info & 3 != 0 {
+        return false;
+    }
+
+    let start = &*(info as *const StartInfo);
+    if start.magic != START_INFO_MAGIC {
+        return false;
+    }
+
+    handoff.acpi_rsdp = start.rsdp_paddr;
+    handoff.set_cmdline(c_str_bytes(start.cmdline_paddr, HANDOFF_CMDLINE_MAX));
+
+    if start.modlist_paddr != 0 {
+        let modules = core::slice::from_raw_parts(
+            start.modlist_paddr as *const ModlistEntry,
+            start.nr_modules as usize,
+        );
+        for module in modules {
+            let name = c_str_bytes(module.cmdline_paddr, HANDOFF_MODULE_NAME_MAX);
+            if !handoff.push_module(module.paddr, module.size, name) {
+                break;
+            }
+        }
+    }
+
+    // The memory map was added in version 1
+    if start.version >= 1 && start.memmap_paddr != 0 {
+        let entries = core::slice::from_raw_parts(
+            start.memmap_paddr as *const MemmapEntry,
+            start.memmap_entries as usize,
+        );
+        fill_memory_map(entries, handoff);
+    }
+
+    true
+}
+
+/// Copy the PVH memory map into the handoff
+pub fn fill_memory_map(entries: &[MemmapEntry], handoff: &mut KernelHandoff) {
+    for entry in entries {
+        if !handoff.push_memory_range(entry.addr, entry.size, e820_type(entry.mem_type)) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_info_layout() {
+        assert_eq!(core::mem::size_of::<StartInfo>(), 56);
+        assert_eq!(core::mem::size_of::<ModlistEntry>(), 32);
+        assert_eq!(core::mem::size_of::<MemmapEntry>(), 24);
+    }
+
+    #[test]
+    fn test_fill_memory_map() {
+        let entries = [
+            MemmapEntry { addr: 0x10_0000, size: 0x100_0000, mem_type: E820_RAM, reserved: 0 },
+            MemmapEntry { addr: 0x0, size: 0x9_fc00, mem_type: E820_RAM, reserved: 0 },
+            MemmapEntry { addr: 0xf_0000, size: 0x1_0000, mem_type: 2, reserved: 0 },
+        ];
+
+        let mut h = KernelHandoff::empty();
+        fill_memory_map(&entries, &mut h);
+
+        let ranges = h.memory_ranges();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].base, 0);
+        assert_eq!(ranges[1].mem_type, MemoryType::Reserved);
+        assert_eq!(ranges[2].mem_type, MemoryType::Available);
+    }
+
+    #[test]
+    fn test_normalize_rejects_bad_magic() {
+        let info = StartInfo {
+            magic: 0,
+            version: 1,
+            flags: 0,
+            nr_modules: 0,
+            modlist_paddr: 0,
+            cmdline_paddr: 0,
+            rsdp_paddr: 0,
+            memmap_paddr: 0,
+            memmap_entries: 0,
+            reserved: 0,
+        };
+
+        let mut h = KernelHandoff::empty();
+        assert!(!unsafe { normalize(&info as *const StartInfo as u64, &mut h) });
+    }
+}
@@ -15,7 +15,26 @@
 //! - **Early output**: Boot-time logging before drivers are ready
 //! - **Per-arch UART drivers**: ARM64, AMD64, RISC-V support
 //! - **Crash dumps**: Register dump, stack trace, panic information
-//! - **Structured logging**: Key-value pair logging format
+//! - **Formatting helpers**: Hex dumps, addresses, sizes, bitfields and
+//!   C `printf` formats, without allocating
+//! - **Structured logging**: Every record carries a timestamp, CPU and
+//!   thread, and goes into the debug log ring shared with debuglog objects
+//!
+//! # Levels
+//!
+//! A message is logged if its level passes two filters:
+//!
+//! - **Compile time**: each module compiles in messages down to a most
+//!   verbose level, from [`MODULE_MAX_LEVELS`] or [`STATIC_MAX_LEVEL`].
+//!   Messages below it cost nothing at run time.
+//! - **Run time**: one global level, set with `kernel.log-level` and the
+//!   `loglevel` shell command. Messages below it are dropped before
+//!   they're formatted.
+//!
+//! # Command Line
+//!
+//! - `kernel.log-level=trace|debug|info|warn|error` - Runtime level
+//!   (default info)
 //!
 //! # Usage
 //!
@@ -27,13 +46,21 @@
 //! // Conditional logging
 //! log_trace_if!(LOCAL_TRACE, "Page fault at {:#x}", fault_addr);
 //!
+//! // Formatting helpers
+//! log_info!("mapped {} at {}", Size(len), Addr(base));
+//! hexdump(&page[..64], base);
+//!
 //! // Panic with diagnostics
 //! panic!("Unexpected state in {}", function_name());
 //! ```
 
 
 use core::fmt::Write;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::kernel::cmdline;
+use crate::rustux::types::Status;
+use crate::kernel::lib::debuglog::{self, DlogHeader, DLOG, DLOG_FLAG_USER, DLOG_MAX_DATA, DLOG_MAX_RECORD};
 
 /// Log levels
 #[repr(u8)]
@@ -62,6 +89,34 @@ pub enum LogLevel {
 pub const INFO: LogLevel = LogLevel::Info;
 
 impl LogLevel {
+    /// Level from its number, as stored in debug log records
+    pub const fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(LogLevel::Trace),
+            1 => Some(LogLevel::Debug),
+            2 => Some(LogLevel::Info),
+            3 => Some(LogLevel::Warning),
+            4 => Some(LogLevel::Error),
+            5 => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+
+    /// Level from its name, in any case
+    pub fn parse(name: &str) -> Option<Self> {
+        [
+            LogLevel::Trace,
+            LogLevel::Debug,
+            LogLevel::Info,
+            LogLevel::Warning,
+            LogLevel::Error,
+            LogLevel::Fatal,
+        ]
+        .into_iter()
+        .find(|level| level.as_str().eq_ignore_ascii_case(name))
+        .or_else(|| name.eq_ignore_ascii_case("warning").then_some(LogLevel::Warning))
+    }
+
     /// Get the log level name as a string
     pub fn as_str(self) -> &'static str {
         match self {
@@ -92,10 +147,76 @@ impl LogLevel {
     }
 }
 
-/// Global minimum log level
+/// Most verbose level compiled in, for modules not in [`MODULE_MAX_LEVELS`]
+pub const STATIC_MAX_LEVEL: LogLevel = if cfg!(debug_assertions) {
+    LogLevel::Trace
+} else {
+    LogLevel::Debug
+};
+
+/// Most verbose level compiled in by module, overriding
+/// [`STATIC_MAX_LEVEL`] for a module and the modules in it
 ///
-/// Only messages at or above this level will be printed.
-static mut MIN_LOG_LEVEL: LogLevel = LogLevel::Info;
+/// Paths leave out the crate name. The first match wins, so list
+/// submodules before their parents. The scheduler, timer tick and user
+/// copies trace every context switch, tick and copy; set them to
+/// `Trace` to debug them.
+pub const MODULE_MAX_LEVELS: &[(&str, LogLevel)] = &[
+    ("kernel::sched", LogLevel::Debug),
+    ("kernel::timer", LogLevel::Debug),
+    ("kernel::usercopy", LogLevel::Debug),
+];
+
+/// Whether `path` is `prefix` or a module inside it
+const fn module_matches(path: &[u8], prefix: &[u8]) -> bool {
+    if path.len() < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if path[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    path.len() == prefix.len() || path[i] == b':'
+}
+
+/// Most verbose level compiled in for the module at `module_path`, as
+/// given by `module_path!()`
+pub const fn module_max_level(module_path: &str) -> LogLevel {
+    // Skip the crate name
+    let path = module_path.as_bytes();
+    let mut start = 0;
+    while start + 1 < path.len() && !(path[start] == b':' && path[start + 1] == b':') {
+        start += 1;
+    }
+    let path = if start + 1 < path.len() { path.split_at(start + 2).1 } else { path };
+
+    let mut i = 0;
+    while i < MODULE_MAX_LEVELS.len() {
+        let (prefix, level) = MODULE_MAX_LEVELS[i];
+        if module_matches(path, prefix.as_bytes()) {
+            return level;
+        }
+        i += 1;
+    }
+    STATIC_MAX_LEVEL
+}
+
+/// Whether messages at `level` are compiled in for the module at
+/// `module_path`
+pub const fn log_compiled_in(level: LogLevel, module_path: &str) -> bool {
+    level as u8 >= module_max_level(module_path) as u8
+}
+
+/// Runtime log level
+///
+/// Only messages at or above this level are logged.
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Command line option for the runtime log level
+const CMDLINE_LOG_LEVEL: &str = "kernel.log-level";
 
 /// Flag indicating whether UART has been initialized
 static mut UART_READY: AtomicBool = AtomicBool::new(false);
@@ -115,12 +236,18 @@ static mut KERNEL_START_TIME: u64 = 0;
 ///
 /// * `level` - Minimum log level to display
 pub fn log_set_min_level(level: LogLevel) {
-    unsafe { MIN_LOG_LEVEL = level; }
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
 }
 
 /// Get the current minimum log level
 pub fn log_get_min_level() -> LogLevel {
-    unsafe { MIN_LOG_LEVEL }
+    LogLevel::from_raw(LOG_LEVEL.load(Ordering::Relaxed)).unwrap_or(LogLevel::Info)
+}
+
+/// Whether messages at `level` pass the runtime level
+#[inline]
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 >= LOG_LEVEL.load(Ordering::Relaxed)
 }
 
 /// Enable or disable colored output
@@ -154,7 +281,9 @@ pub fn log_set_kernel_start_time(time: u64) {
 ///
 /// This is the core output function that writes to the console.
 /// It will use early boot output before UART is ready, and
-/// switch to UART output once available.
+/// switch to UART output once available. Output is also mirrored to
+/// the framebuffer console if the loader provided a framebuffer, and to
+/// the crash log while a panic is being recorded.
 ///
 /// # Arguments
 ///
@@ -175,10 +304,49 @@ pub(crate) fn print_internal(s: &str) {
             early_print(s);
         }
     }
+
+    // Mirror to the framebuffer console when one is available
+    crate::kernel::dev::fbcon::write_str(s);
+
+    crate::kernel::lib::crashlog::record(s);
+}
+
+/// The longest valid UTF-8 prefix of `bytes`
+fn utf8_prefix(bytes: &[u8]) -> &str {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => unsafe { core::str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+    }
+}
+
+/// Print the prefix of a record's console line: its level, then when,
+/// where and by which thread it was written
+fn print_record_prefix(level: LogLevel, header: &DlogHeader) {
+    let colors = unsafe { USE_COLORS.load(Ordering::Relaxed) };
+    if colors {
+        print_internal(level.as_ansi_color());
+    }
+    print_internal("[");
+    print_internal(level.as_str());
+    print_internal("]");
+    if colors {
+        print_internal(LogLevel::ansi_reset());
+    }
+
+    if unsafe { SHOW_TIMESTAMPS.load(Ordering::Relaxed) } {
+        let us = header.timestamp / 1_000;
+        let _ = write!(LogWriter, " [{:5}.{:06} {}:{}] ", us / 1_000_000, us % 1_000_000, header.cpu, header.tid);
+    } else {
+        let _ = write!(LogWriter, " [{}:{}] ", header.cpu, header.tid);
+    }
 }
 
 /// Print a formatted message at a specific log level
 ///
+/// Records the message in the debug log, truncated to `DLOG_MAX_DATA`
+/// bytes, and prints all of it to the console. The logging macros call
+/// this for messages their module compiles in.
+///
 /// # Arguments
 ///
 /// * `level` - Log level for this message
@@ -186,41 +354,34 @@ pub(crate) fn print_internal(s: &str) {
 #[inline]
 pub fn log_print(level: LogLevel, args: core::fmt::Arguments) {
     // Check if this message should be logged
-    unsafe {
-        if level < MIN_LOG_LEVEL {
-            return;
-        }
+    if !log_enabled(level) {
+        return;
     }
 
-    // Print log level
-    let color = if unsafe { USE_COLORS.load(Ordering::Relaxed) } {
-        level.as_ansi_color()
-    } else {
-        ""
-    };
+    let header = DlogHeader::new(level as u16);
+    let mut text = FmtBuf::<DLOG_MAX_DATA>::new();
+    let _ = text.write_fmt(args);
+    let _ = DLOG.write_stamped(header, text.as_bytes());
 
-    print_internal(color);
-    print_internal("[");
-    print_internal(level.as_str());
-    print_internal("]");
-    if unsafe { USE_COLORS.load(Ordering::Relaxed) } {
-        print_internal(LogLevel::ansi_reset());
-    }
-    print_internal(" ");
+    print_record_prefix(level, &header);
+    let _ = LogWriter.write_fmt(args);
+    print_internal("\n");
+}
 
-    // Print timestamp if enabled
-    if unsafe { SHOW_TIMESTAMPS.load(Ordering::Relaxed) } {
-        // TODO: Get actual timestamp
-        print_internal("[T+0.000000] ");
+/// Log text a debuglog object's owner wrote
+///
+/// `flags` are `DLOG_FLAG_*` bits; the level is taken from them.
+pub fn log_write_user(flags: u16, text: &[u8]) -> Result<(), Status> {
+    let header = DlogHeader::new(flags | DLOG_FLAG_USER);
+    DLOG.write_stamped(header, text)?;
+
+    let level = LogLevel::from_raw(header.level()).unwrap_or(LogLevel::Info);
+    if log_enabled(level) {
+        print_record_prefix(level, &header);
+        print_internal(utf8_prefix(text));
+        print_internal("\n");
     }
-
-    // Print the formatted message
-    // Note: We need to collect the output into a buffer first
-    // For now, we'll use a simple approach
-    let _ = write!(LogWriter, "{}", args);
-
-    // Print newline
-    print_internal("\n");
+    Ok(())
 }
 
 /// Writer for logging
@@ -233,11 +394,25 @@ impl core::fmt::Write for LogWriter {
     }
 }
 
+/// Log a message at a level if the calling module compiles it in
+///
+/// The compile-time check is a constant, so messages a module leaves out
+/// are not compiled at all.
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => {{
+        const COMPILED_IN: bool = $crate::kernel::debug::log_compiled_in($level, module_path!());
+        if COMPILED_IN {
+            $crate::kernel::debug::log_print($level, format_args!($($arg)*));
+        }
+    }};
+}
+
 /// Log a trace message
 #[macro_export]
 macro_rules! log_trace {
     ($($arg:tt)*) => {
-        $crate::kernel::debug::log_print($crate::kernel::debug::LogLevel::Trace, format_args!($($arg)*));
+        $crate::log_at!($crate::kernel::debug::LogLevel::Trace, $($arg)*)
     };
 }
 
@@ -255,7 +430,7 @@ macro_rules! log_trace_if {
 #[macro_export]
 macro_rules! log_debug {
     ($($arg:tt)*) => {
-        $crate::kernel::debug::log_print($crate::kernel::debug::LogLevel::Debug, format_args!($($arg)*));
+        $crate::log_at!($crate::kernel::debug::LogLevel::Debug, $($arg)*)
     };
 }
 
@@ -263,7 +438,7 @@ macro_rules! log_debug {
 #[macro_export]
 macro_rules! log_info {
     ($($arg:tt)*) => {
-        $crate::kernel::debug::log_print($crate::kernel::debug::LogLevel::Info, format_args!($($arg)*));
+        $crate::log_at!($crate::kernel::debug::LogLevel::Info, $($arg)*)
     };
 }
 
@@ -271,7 +446,7 @@ macro_rules! log_info {
 #[macro_export]
 macro_rules! log_warn {
     ($($arg:tt)*) => {
-        $crate::kernel::debug::log_print($crate::kernel::debug::LogLevel::Warning, format_args!($($arg)*));
+        $crate::log_at!($crate::kernel::debug::LogLevel::Warning, $($arg)*)
     };
 }
 
@@ -279,7 +454,7 @@ macro_rules! log_warn {
 #[macro_export]
 macro_rules! log_error {
     ($($arg:tt)*) => {
-        $crate::kernel::debug::log_print($crate::kernel::debug::LogLevel::Error, format_args!($($arg)*));
+        $crate::log_at!($crate::kernel::debug::LogLevel::Error, $($arg)*)
     };
 }
 
@@ -320,40 +495,7 @@ pub fn log_fatal(args: core::fmt::Arguments) -> ! {
 /// * `col` - Column number
 #[inline(never)]
 pub fn panic_handler(message: &str, file: &str, line: u32, col: u32) -> ! {
-    extern "C" {
-        /// Dump CPU registers
-        fn dump_registers();
-
-        /// Dump stack trace
-        fn dump_stack_trace();
-
-        /// Halt all CPUs
-        fn halt_all_cpus();
-    }
-
-    log_print(LogLevel::Fatal, format_args!("KERNEL PANIC"));
-
-    if !message.is_empty() {
-        log_print(LogLevel::Fatal, format_args!("  Message: {}", message));
-    }
-
-    log_print(LogLevel::Fatal, format_args!("  Location: {}:{}:{}", file, line, col));
-
-    // Print register dump
-    log_print(LogLevel::Fatal, format_args!(""));
-    log_print(LogLevel::Fatal, format_args!("Register Dump:"));
-    unsafe { dump_registers(); }
-
-    // Print stack trace
-    log_print(LogLevel::Fatal, format_args!(""));
-    log_print(LogLevel::Fatal, format_args!("Stack Trace:"));
-    unsafe { dump_stack_trace(); }
-
-    // Halt the system
-    unsafe { halt_all_cpus(); }
-
-    // Should never reach here
-    loop {}
+    crate::kernel::panic::panic_at(message, file, line, col)
 }
 
 /// Assert handler
@@ -433,6 +575,19 @@ pub fn log_init() {
     log_info!("Rustux kernel logging initialized");
 }
 
+/// Set the runtime level from the command line
+///
+/// Called once the command line is parsed.
+pub fn log_init_cmdline() {
+    let Some(name) = cmdline::cmdline_get(CMDLINE_LOG_LEVEL) else {
+        return;
+    };
+    match LogLevel::parse(name) {
+        Some(level) => log_set_min_level(level),
+        None => log_warn!("{}: unknown level '{}'", CMDLINE_LOG_LEVEL, name),
+    }
+}
+
 /// Initialize logging after UART is ready
 pub fn log_init_uart() {
     log_set_uart_ready();
@@ -444,6 +599,71 @@ pub fn init() {
     log_init();
 }
 
+/// `loglevel` console command
+fn cmd_loglevel(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    if argc >= 2 {
+        match LogLevel::parse(argv[1]) {
+            Some(level) => log_set_min_level(level),
+            None => {
+                crate::println!("usage: loglevel [trace|debug|info|warn|error]");
+                return -1;
+            }
+        }
+    }
+    crate::println!(
+        "log level {}, compiled in down to {}",
+        log_get_min_level().as_str(),
+        STATIC_MAX_LEVEL.as_str()
+    );
+    for (module, level) in MODULE_MAX_LEVELS {
+        crate::println!("  {:<24} down to {}", module, level.as_str());
+    }
+    0
+}
+
+crate::static_command!("loglevel", "show or set the log level [level]", cmd_loglevel);
+
+/// `dlog` console command
+fn cmd_dlog(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    let min = match argv.get(1) {
+        Some(name) if argc >= 2 => match LogLevel::parse(name) {
+            Some(level) => level,
+            None => {
+                crate::println!("usage: dlog [level]");
+                return -1;
+            }
+        },
+        _ => LogLevel::Trace,
+    };
+
+    let mut reader = DLOG.reader();
+    let mut record = [0u8; DLOG_MAX_RECORD];
+    while let Ok(len) = DLOG.read(&mut reader, &mut record) {
+        let Some((header, text)) = debuglog::parse_record(&record[..len]) else {
+            continue;
+        };
+        let level = LogLevel::from_raw(header.level()).unwrap_or(LogLevel::Info);
+        if level < min {
+            continue;
+        }
+        let us = header.timestamp / 1_000;
+        let source = if header.flags & DLOG_FLAG_USER != 0 { "user" } else { "kernel" };
+        crate::println!(
+            "[{:5}.{:06} {}:{}] {:<5} {:<6} {}",
+            us / 1_000_000,
+            us % 1_000_000,
+            header.cpu,
+            header.tid,
+            level.as_str(),
+            source,
+            utf8_prefix(text)
+        );
+    }
+    0
+}
+
+crate::static_command!("dlog", "dump the debug log ring [min level]", cmd_dlog);
+
 // ============================================================================
 // LK Compatibility Functions
 // ============================================================================
@@ -467,14 +687,506 @@ macro_rules! dprintf {
     };
 }
 
-/// Hex dump with extended info (LK compatibility)
-pub fn hexdump_ex(_ptr: *const u8, _len: usize, _width: usize) {
-    // TODO: Implement hex dump
-}
-
 /// dprintf function variant (LK compatibility)
 /// For cases where a function is needed instead of the macro
 pub fn dprintf(_level: LogLevel, _fmt: &str) {
     // Function variant just logs using the macro internally
     // Use the macro directly for better formatting support
 }
+
+// ============================================================================
+// Formatting Helpers
+// ============================================================================
+//
+// None of these allocate, so the panic handler and code running with
+// locks held can use them. Each is a `Display` wrapper, printed with the
+// usual macros, or written into a `FmtBuf` on the stack.
+
+/// A fixed-size text buffer on the stack
+///
+/// Writes past the end are cut at a character boundary, so the text is
+/// always valid UTF-8.
+pub struct FmtBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> FmtBuf<N> {
+    /// An empty buffer
+    pub const fn new() -> Self {
+        Self { buf: [0; N], len: 0, truncated: false }
+    }
+
+    /// The text written so far
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are ever copied in
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+
+    /// The text written so far, as bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Whether a write didn't fit
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Empty the buffer
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+}
+
+impl<const N: usize> core::fmt::Write for FmtBuf<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut n = s.len().min(N - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        self.truncated |= n < s.len();
+        Ok(())
+    }
+}
+
+/// Hex dump of bytes, 16 to a line, with the address of each line and
+/// the bytes as ASCII
+///
+/// Runs of identical lines print as one line and a `*`, like
+/// `hexdump -C`. The last line always prints, so the dump shows where
+/// the bytes end.
+///
+/// ```text
+/// ffff800000201000  41 42 43 44 00 00 00 00  00 00 00 00 00 00 00 00  |ABCD............|
+/// ```
+pub struct Hexdump<'a> {
+    data: &'a [u8],
+    addr: u64,
+}
+
+impl<'a> Hexdump<'a> {
+    /// Dump `data`, numbering lines from `addr`
+    pub fn new(data: &'a [u8], addr: u64) -> Self {
+        Self { data, addr }
+    }
+}
+
+impl core::fmt::Display for Hexdump<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let end = self.addr.saturating_add(self.data.len() as u64);
+        let width = if end > u32::MAX as u64 { 16 } else { 8 };
+        let lines = self.data.len().div_ceil(16);
+
+        let mut prev: Option<&[u8]> = None;
+        let mut starred = false;
+        for (i, line) in self.data.chunks(16).enumerate() {
+            if prev == Some(line) && line.len() == 16 && i + 1 < lines {
+                if !starred {
+                    writeln!(f, "*")?;
+                    starred = true;
+                }
+                continue;
+            }
+            prev = Some(line);
+            starred = false;
+
+            write!(f, "{:0width$x} ", self.addr.wrapping_add(i as u64 * 16), width = width)?;
+            for j in 0..16 {
+                if j == 8 {
+                    write!(f, " ")?;
+                }
+                match line.get(j) {
+                    Some(b) => write!(f, " {:02x}", b)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            write!(f, "  |")?;
+            for &b in line {
+                let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f, "|")?;
+        }
+        Ok(())
+    }
+}
+
+/// Print a hex dump of `data` to the console, numbering lines from `addr`
+///
+/// See [`Hexdump`] for the format.
+pub fn hexdump(data: &[u8], addr: u64) {
+    let _ = write!(LogWriter, "{}", Hexdump::new(data, addr));
+}
+
+/// An address, as 16 hex digits
+///
+/// Pads to the formatter's width, so `{:>20}` lines up a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Addr(pub u64);
+
+impl<T> From<*const T> for Addr {
+    fn from(ptr: *const T) -> Self {
+        Addr(ptr as usize as u64)
+    }
+}
+
+impl core::fmt::Display for Addr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut buf = FmtBuf::<18>::new();
+        let _ = write!(buf, "{:#018x}", self.0);
+        f.pad(buf.as_str())
+    }
+}
+
+/// A size in bytes, in binary units: `512 B`, `4 KiB`, `1.5 MiB`
+///
+/// Sizes that aren't a whole number of units show one decimal, rounded
+/// down. Pads to the formatter's width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size(pub u64);
+
+impl core::fmt::Display for Size {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        let mut value = self.0;
+        let mut rem = 0;
+        let mut unit = 0;
+        while value >= 1024 && unit + 1 < UNITS.len() {
+            rem = value % 1024;
+            value /= 1024;
+            unit += 1;
+        }
+
+        let mut buf = FmtBuf::<16>::new();
+        let tenths = rem * 10 / 1024;
+        if tenths == 0 {
+            let _ = write!(buf, "{} {}", value, UNITS[unit]);
+        } else {
+            let _ = write!(buf, "{}.{} {}", value, tenths, UNITS[unit]);
+        }
+        f.pad(buf.as_str())
+    }
+}
+
+/// A bitfield with the names of its set bits: `0x13 <PRESENT|WRITE|0x10>`
+///
+/// `names` pairs a mask with its name; a mask of several bits is named
+/// when all of them are set. Set bits without a name print in hex at the
+/// end.
+pub struct Bits<'a> {
+    value: u64,
+    names: &'a [(u64, &'a str)],
+}
+
+impl<'a> Bits<'a> {
+    /// Decode `value` with `names`
+    pub fn new(value: u64, names: &'a [(u64, &'a str)]) -> Self {
+        Self { value, names }
+    }
+}
+
+impl core::fmt::Display for Bits<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#x}", self.value)?;
+        if self.value == 0 {
+            return Ok(());
+        }
+
+        let mut sep = " <";
+        let mut left = self.value;
+        for &(mask, name) in self.names {
+            if mask != 0 && self.value & mask == mask {
+                write!(f, "{}{}", sep, name)?;
+                sep = "|";
+                left &= !mask;
+            }
+        }
+        if left != 0 {
+            write!(f, "{}{:#x}", sep, left)?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// An argument to [`Printf`]
+#[derive(Debug, Clone, Copy)]
+pub enum PrintfArg<'a> {
+    /// For `%d` and `%i`; also printed by the unsigned conversions
+    Int(i64),
+    /// For `%u`, `%x`, `%X` and `%o`
+    Uint(u64),
+    /// For `%s`
+    Str(&'a str),
+    /// For `%c`
+    Char(char),
+    /// For `%p`
+    Ptr(usize),
+}
+
+/// C `printf` formatting, for messages and tables ported from C
+///
+/// Supports the `-`, `0`, `+`, space and `#` flags, a width and
+/// precision (either may be `*`), the `d i u x X o c s p %` conversions,
+/// and ignores length modifiers, as arguments are already 64 bits. A
+/// missing or mistyped argument prints as `(?)`.
+pub struct Printf<'a> {
+    fmt: &'a str,
+    args: &'a [PrintfArg<'a>],
+}
+
+impl<'a> Printf<'a> {
+    /// Format `args` with `fmt`
+    pub fn new(fmt: &'a str, args: &'a [PrintfArg<'a>]) -> Self {
+        Self { fmt, args }
+    }
+}
+
+/// A `printf` conversion's flags, width and precision
+#[derive(Default)]
+struct PrintfSpec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl PrintfSpec {
+    /// Write `prefix` (sign or radix prefix) then `body`, padded to the
+    /// width
+    fn pad(&self, f: &mut core::fmt::Formatter<'_>, prefix: &str, body: &str) -> core::fmt::Result {
+        let len = prefix.len() + body.chars().count();
+        let fill = self.width.saturating_sub(len);
+        if self.left {
+            write!(f, "{}{}{:fill$}", prefix, body, "", fill = fill)
+        } else if self.zero && self.precision.is_none() {
+            write!(f, "{}{:0>fill$}{}", prefix, "", body, fill = fill)
+        } else {
+            write!(f, "{:fill$}{}{}", "", prefix, body, fill = fill)
+        }
+    }
+}
+
+/// Longest precision honoured, which bounds the digits of a conversion
+const PRINTF_MAX_PRECISION: usize = 64;
+
+/// The next `printf` argument as a count, for `*`
+fn printf_count(args: &mut core::slice::Iter<'_, PrintfArg<'_>>) -> Option<i64> {
+    match args.next() {
+        Some(PrintfArg::Int(n)) => Some(*n),
+        Some(PrintfArg::Uint(n)) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+impl core::fmt::Display for Printf<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut args = self.args.iter();
+        let mut chars = self.fmt.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                write!(f, "{}", c)?;
+                continue;
+            }
+
+            let mut spec = PrintfSpec::default();
+            while let Some(&flag) = chars.peek() {
+                match flag {
+                    '-' => spec.left = true,
+                    '0' => spec.zero = true,
+                    '+' => spec.plus = true,
+                    ' ' => spec.space = true,
+                    '#' => spec.alt = true,
+                    _ => break,
+                }
+                chars.next();
+            }
+
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                let width = printf_count(&mut args).unwrap_or(0);
+                spec.left |= width < 0;
+                spec.width = width.unsigned_abs() as usize;
+            } else {
+                while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    spec.width = spec.width.saturating_mul(10).saturating_add(d as usize);
+                    chars.next();
+                }
+            }
+
+            if chars.peek() == Some(&'.') {
+                chars.next();
+                let mut precision = 0usize;
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    precision = printf_count(&mut args).unwrap_or(0).max(0) as usize;
+                } else {
+                    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        precision = precision.saturating_mul(10).saturating_add(d as usize);
+                        chars.next();
+                    }
+                }
+                spec.precision = Some(precision);
+            }
+
+            while matches!(chars.peek(), Some('h' | 'l' | 'z' | 'j' | 't' | 'L' | 'q')) {
+                chars.next();
+            }
+
+            let Some(conv) = chars.next() else {
+                return write!(f, "%");
+            };
+            if conv == '%' {
+                write!(f, "%")?;
+                continue;
+            }
+
+            let arg = args.next().copied();
+            match (conv, arg) {
+                ('s', Some(PrintfArg::Str(s))) => {
+                    let s = match spec.precision {
+                        Some(p) => s.char_indices().nth(p).map_or(s, |(i, _)| &s[..i]),
+                        None => s,
+                    };
+                    spec.pad(f, "", s)?;
+                }
+                ('c', Some(PrintfArg::Char(c))) => {
+                    let mut buf = [0u8; 4];
+                    spec.pad(f, "", c.encode_utf8(&mut buf))?;
+                }
+                ('d' | 'i' | 'u' | 'x' | 'X' | 'o' | 'p', Some(arg)) => {
+                    let (negative, value) = match (conv, arg) {
+                        ('d' | 'i', PrintfArg::Int(n)) => (n < 0, n.unsigned_abs()),
+                        ('d' | 'i', PrintfArg::Uint(n)) => (false, n),
+                        (_, PrintfArg::Int(n)) => (false, n as u64),
+                        (_, PrintfArg::Uint(n)) => (false, n),
+                        (_, PrintfArg::Ptr(p)) => (false, p as u64),
+                        _ => {
+                            spec.pad(f, "", "(?)")?;
+                            continue;
+                        }
+                    };
+
+                    let mut digits = FmtBuf::<{ PRINTF_MAX_PRECISION + 24 }>::new();
+                    let min = spec.precision.unwrap_or(1).min(PRINTF_MAX_PRECISION);
+                    let _ = match conv {
+                        'x' | 'p' => write!(digits, "{:0min$x}", value, min = min),
+                        'X' => write!(digits, "{:0min$X}", value, min = min),
+                        'o' => write!(digits, "{:0min$o}", value, min = min),
+                        _ => write!(digits, "{:0min$}", value, min = min),
+                    };
+                    // A precision of 0 prints nothing for 0
+                    let body = if min == 0 && value == 0 { "" } else { digits.as_str() };
+
+                    let prefix = match conv {
+                        'd' | 'i' if negative => "-",
+                        'd' | 'i' if spec.plus => "+",
+                        'd' | 'i' if spec.space => " ",
+                        'p' => "0x",
+                        'x' if spec.alt && value != 0 => "0x",
+                        'X' if spec.alt && value != 0 => "0X",
+                        'o' if spec.alt && !body.starts_with('0') => "0",
+                        _ => "",
+                    };
+                    spec.pad(f, prefix, body)?;
+                }
+                _ => spec.pad(f, "", "(?)")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_parse() {
+        assert_eq!(LogLevel::parse("debug"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::parse("WARN"), Some(LogLevel::Warning));
+        assert_eq!(LogLevel::parse("warning"), Some(LogLevel::Warning));
+        assert_eq!(LogLevel::parse("loud"), None);
+        assert_eq!(LogLevel::from_raw(LogLevel::Error as u8), Some(LogLevel::Error));
+        assert_eq!(LogLevel::from_raw(6), None);
+    }
+
+    #[test]
+    fn test_module_max_level() {
+        assert_eq!(module_max_level("rustux::kernel::sched"), LogLevel::Debug);
+        assert_eq!(module_max_level("rustux::kernel::sched::deadline"), LogLevel::Debug);
+        assert_eq!(module_max_level("rustux::kernel::scheduler"), STATIC_MAX_LEVEL);
+        assert_eq!(module_max_level("rustux::kernel::vm"), STATIC_MAX_LEVEL);
+        assert!(!log_compiled_in(LogLevel::Trace, "rustux::kernel::timer"));
+        assert!(log_compiled_in(LogLevel::Error, "rustux::kernel::timer"));
+    }
+
+    #[test]
+    fn test_fmt_buf_truncates_whole_chars() {
+        let mut buf = FmtBuf::<5>::new();
+        let _ = write!(buf, "abcdé");
+        assert_eq!(buf.as_str(), "abcd");
+        assert!(buf.is_truncated());
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let mut data = [0u8; 64];
+        data[..4].copy_from_slice(b"ABCD");
+        data[63] = 0xff;
+        let mut buf = FmtBuf::<512>::new();
+        let _ = write!(buf, "{}", Hexdump::new(&data, 0x1000));
+
+        let mut lines = buf.as_str().lines();
+        assert_eq!(
+            lines.next(),
+            Some("00001000  41 42 43 44 00 00 00 00  00 00 00 00 00 00 00 00  |ABCD............|")
+        );
+        assert_eq!(lines.next().map(|l| &l[..8]), Some("00001010"));
+        assert_eq!(lines.next(), Some("*"));
+        assert_eq!(lines.next().map(|l| &l[..8]), Some("00001030"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_size_and_addr() {
+        let mut buf = FmtBuf::<64>::new();
+        let _ = write!(buf, "{}|{}|{}|{:>8}", Size(512), Size(4096), Size(3 * 512 * 1024), Size(1 << 30));
+        assert_eq!(buf.as_str(), "512 B|4 KiB|1.5 MiB|   1 GiB");
+
+        buf.clear();
+        let _ = write!(buf, "{}", Addr(0xffff_8000_0010_0000));
+        assert_eq!(buf.as_str(), "0xffff800000100000");
+    }
+
+    #[test]
+    fn test_bits() {
+        const NAMES: &[(u64, &str)] = &[(1 << 0, "PRESENT"), (1 << 1, "WRITE"), (1 << 2, "USER")];
+        let mut buf = FmtBuf::<64>::new();
+        let _ = write!(buf, "{} {}", Bits::new(0x13, NAMES), Bits::new(0, NAMES));
+        assert_eq!(buf.as_str(), "0x13 <PRESENT|WRITE|0x10> 0x0");
+    }
+
+    #[test]
+    fn test_printf() {
+        let mut buf = FmtBuf::<128>::new();
+        let args = [
+            PrintfArg::Int(-42),
+            PrintfArg::Uint(0xbeef),
+            PrintfArg::Str("kernel"),
+            PrintfArg::Int(7),
+            PrintfArg::Ptr(0x1000),
+        ];
+        let _ = write!(buf, "{}", Printf::new("[%5d] [%#06x] [%-8.3s] [%03u] %p %d%%", &args));
+        assert_eq!(buf.as_str(), "[  -42] [0xbeef] [ker     ] [007] 0x1000 (?)%");
+    }
+}
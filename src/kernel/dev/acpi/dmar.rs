@@ -0,0 +1,243 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! DMAR (DMA Remapping) Table
+//!
+//! Describes the Intel VT-d remapping hardware units (DRHDs): where each
+//! unit's registers are and which PCI devices sit behind it. A unit
+//! either lists its devices, or covers every device of its segment that
+//! no other unit lists. The table signature is "DMAR".
+
+use super::tables::{read_u16, read_u64, read_u8, Table, SDT_HEADER_LEN};
+
+/// DMAR signature
+pub const DMAR_SIGNATURE: &[u8; 4] = b"DMAR";
+
+/// Maximum remapping units recorded
+pub const MAX_DMAR_UNITS: usize = 8;
+
+/// Maximum device scopes recorded per unit
+pub const MAX_DMAR_SCOPES: usize = 16;
+
+/// Remapping structure types
+const STRUCT_DRHD: u16 = 0;
+
+/// DRHD flags
+const DRHD_INCLUDE_PCI_ALL: u8 = 1 << 0;
+
+/// Device scope types
+const SCOPE_PCI_ENDPOINT: u8 = 1;
+const SCOPE_PCI_SUBHIERARCHY: u8 = 2;
+
+/// A PCI device (or bridge, for its whole subhierarchy) behind a unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceScope {
+    /// Bus of the device
+    pub bus: u8,
+
+    /// Device and function, as `dev << 3 | func`
+    pub devfn: u8,
+
+    /// Whether the scope covers everything below a bridge
+    pub subhierarchy: bool,
+}
+
+impl DeviceScope {
+    const EMPTY: Self = Self { bus: 0, devfn: 0, subhierarchy: false };
+
+    /// Requester ID (`bus << 8 | devfn`)
+    pub const fn rid(&self) -> u16 {
+        (self.bus as u16) << 8 | self.devfn as u16
+    }
+}
+
+/// One remapping hardware unit
+#[derive(Debug, Clone, Copy)]
+pub struct DrhdUnit {
+    /// Physical base of the register set
+    pub base: u64,
+
+    /// PCI segment
+    pub segment: u16,
+
+    /// Covers every device of the segment not listed by another unit
+    pub include_all: bool,
+
+    scopes: [DeviceScope; MAX_DMAR_SCOPES],
+    scope_count: usize,
+}
+
+impl DrhdUnit {
+    const EMPTY: Self = Self {
+        base: 0,
+        segment: 0,
+        include_all: false,
+        scopes: [DeviceScope::EMPTY; MAX_DMAR_SCOPES],
+        scope_count: 0,
+    };
+
+    /// Devices listed for the unit
+    pub fn scopes(&self) -> &[DeviceScope] {
+        &self.scopes[..self.scope_count]
+    }
+
+    /// Whether the unit lists requester `rid`
+    ///
+    /// Only the first path element of a scope is recorded, so a
+    /// subhierarchy scope is taken to cover the buses numbered after its
+    /// bridge's bus.
+    fn lists(&self, rid: u16) -> bool {
+        self.scopes().iter().any(|scope| {
+            scope.rid() == rid || (scope.subhierarchy && (rid >> 8) as u8 > scope.bus)
+        })
+    }
+}
+
+/// Parsed DMAR
+#[derive(Debug, Clone, Copy)]
+pub struct Dmar {
+    /// Width of DMA addresses the platform supports, in bits
+    pub host_address_width: u8,
+
+    units: [DrhdUnit; MAX_DMAR_UNITS],
+    unit_count: usize,
+}
+
+impl Dmar {
+    /// Empty DMAR
+    pub const fn new() -> Self {
+        Self {
+            host_address_width: 0,
+            units: [DrhdUnit::EMPTY; MAX_DMAR_UNITS],
+            unit_count: 0,
+        }
+    }
+
+    /// Parse a validated DMAR
+    ///
+    /// Structures other than DRHDs (reserved memory, ATSR and the like)
+    /// are skipped.
+    pub fn parse(table: &Table) -> Self {
+        let bytes = table.bytes();
+        let mut dmar = Self::new();
+        // The field holds the width minus one
+        dmar.host_address_width = read_u8(bytes, SDT_HEADER_LEN).saturating_add(1);
+
+        // Flags and 10 reserved bytes follow the width
+        let mut off = SDT_HEADER_LEN + 12;
+        while off + 4 <= bytes.len() {
+            let kind = read_u16(bytes, off);
+            let len = read_u16(bytes, off + 2) as usize;
+            if len < 4 || off + len > bytes.len() {
+                break;
+            }
+
+            if kind == STRUCT_DRHD && len >= 16 && dmar.unit_count < MAX_DMAR_UNITS {
+                dmar.units[dmar.unit_count] = Self::parse_drhd(&bytes[off..off + len]);
+                dmar.unit_count += 1;
+            }
+            off += len;
+        }
+
+        dmar
+    }
+
+    fn parse_drhd(entry: &[u8]) -> DrhdUnit {
+        let mut unit = DrhdUnit {
+            base: read_u64(entry, 8),
+            segment: read_u16(entry, 6),
+            include_all: read_u8(entry, 4) & DRHD_INCLUDE_PCI_ALL != 0,
+            ..DrhdUnit::EMPTY
+        };
+
+        let mut off = 16;
+        while off + 6 <= entry.len() {
+            let kind = read_u8(entry, off);
+            let len = read_u8(entry, off + 1) as usize;
+            if len < 6 || off + len > entry.len() {
+                break;
+            }
+
+            if (kind == SCOPE_PCI_ENDPOINT || kind == SCOPE_PCI_SUBHIERARCHY)
+                && len >= 8
+                && unit.scope_count < MAX_DMAR_SCOPES
+            {
+                let dev = read_u8(entry, off + 6);
+                let func = read_u8(entry, off + 7);
+                unit.scopes[unit.scope_count] = DeviceScope {
+                    bus: read_u8(entry, off + 5),
+                    devfn: (dev & 0x1F) << 3 | (func & 0x7),
+                    subhierarchy: kind == SCOPE_PCI_SUBHIERARCHY,
+                };
+                unit.scope_count += 1;
+            }
+            off += len;
+        }
+
+        unit
+    }
+
+    /// All remapping units
+    pub fn units(&self) -> &[DrhdUnit] {
+        &self.units[..self.unit_count]
+    }
+
+    /// Index of the unit translating requester `rid` on `segment`
+    pub fn unit_for(&self, segment: u16, rid: u16) -> Option<usize> {
+        let units = self.units();
+        units
+            .iter()
+            .position(|u| u.segment == segment && !u.include_all && u.lists(rid))
+            .or_else(|| units.iter().position(|u| u.segment == segment && u.include_all))
+    }
+}
+
+impl Default for Dmar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_dmar_parse() {
+        let mut buf = [0u8; 128];
+        buf[SDT_HEADER_LEN] = 38;
+        let mut off = SDT_HEADER_LEN + 12;
+
+        // Unit listing the graphics device 00:02.0
+        buf[off + 2] = 24;
+        buf[off + 8..off + 16].copy_from_slice(&0xfed9_0000u64.to_le_bytes());
+        buf[off + 16] = SCOPE_PCI_ENDPOINT;
+        buf[off + 17] = 8;
+        buf[off + 22] = 2;
+        off += 24;
+
+        // Catch-all unit for segment 0
+        buf[off + 2] = 16;
+        buf[off + 4] = DRHD_INCLUDE_PCI_ALL;
+        buf[off + 8..off + 16].copy_from_slice(&0xfed9_1000u64.to_le_bytes());
+        off += 16;
+
+        write_header(&mut buf, DMAR_SIGNATURE, off);
+        fix_checksum(&mut buf[..off], 9);
+
+        let dmar = Dmar::parse(&Table::parse(&buf[..off]).unwrap());
+        assert_eq!(dmar.host_address_width, 39);
+        assert_eq!(dmar.units().len(), 2);
+        assert_eq!(dmar.units()[0].base, 0xfed9_0000);
+        assert_eq!(dmar.units()[0].scopes(), &[DeviceScope { bus: 0, devfn: 0x10, subhierarchy: false }]);
+        assert!(dmar.units()[1].include_all);
+
+        assert_eq!(dmar.unit_for(0, 0x0010), Some(0));
+        assert_eq!(dmar.unit_for(0, 0x0300), Some(1));
+        assert_eq!(dmar.unit_for(1, 0x0010), None);
+    }
+}
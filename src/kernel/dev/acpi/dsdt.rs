@@ -0,0 +1,133 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! DSDT Sleep Types
+//!
+//! Soft-off (S5) is entered by writing the `\_S5` sleep type to the PM1
+//! control registers. `\_S5` is an AML package in the DSDT; without an AML
+//! interpreter it is found by its encoding, which firmware always emits
+//! the same way:
+//!
+//! ```text
+//! 08 [5C] 5F 53 35 5F 12 <PkgLength> <NumElements> <SLP_TYPa> <SLP_TYPb> ...
+//! NameOp [\] "_S5_"   PackageOp
+//! ```
+//!
+//! Each sleep type is an integer: `ZeroOp`, `OneOp` or a `BytePrefix`,
+//! `WordPrefix` or `DWordPrefix` constant.
+
+use super::tables::read_u8;
+
+/// PM1 control: SCI enabled (the platform is in ACPI mode)
+pub const PM1_SCI_EN: u16 = 1 << 0;
+
+/// PM1 control: sleep type field
+pub const PM1_SLP_TYP_SHIFT: u16 = 10;
+pub const PM1_SLP_TYP_MASK: u16 = 0x7 << PM1_SLP_TYP_SHIFT;
+
+/// PM1 control: enter the sleep state in SLP_TYP
+pub const PM1_SLP_EN: u16 = 1 << 13;
+
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+const AML_NAME_OP: u8 = 0x08;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_WORD_PREFIX: u8 = 0x0B;
+const AML_DWORD_PREFIX: u8 = 0x0C;
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_ROOT_CHAR: u8 = 0x5C;
+
+/// Values to write to SLP_TYP in PM1a and PM1b control to enter a state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepType {
+    /// SLP_TYP for PM1a control
+    pub a: u8,
+
+    /// SLP_TYP for PM1b control
+    pub b: u8,
+}
+
+/// PM1 control value that enters sleep type `typ`, keeping the other bits
+/// of `current`
+pub fn pm1_control(current: u16, typ: u8) -> u16 {
+    (current & !(PM1_SLP_TYP_MASK | PM1_SLP_EN))
+        | (((typ as u16) << PM1_SLP_TYP_SHIFT) & PM1_SLP_TYP_MASK)
+        | PM1_SLP_EN
+}
+
+/// Decode one AML integer at `off`, returning it and its encoded length
+fn aml_integer(aml: &[u8], off: usize) -> Option<(u8, usize)> {
+    match *aml.get(off)? {
+        AML_ZERO_OP => Some((0, 1)),
+        AML_ONE_OP => Some((1, 1)),
+        AML_BYTE_PREFIX => Some((*aml.get(off + 1)?, 2)),
+        AML_WORD_PREFIX => Some((*aml.get(off + 1)?, 3)),
+        AML_DWORD_PREFIX => Some((*aml.get(off + 1)?, 5)),
+        _ => None,
+    }
+}
+
+/// Find the `\_S5` sleep type in the AML of a DSDT, after its header
+pub fn find_s5(aml: &[u8]) -> Option<SleepType> {
+    let names = aml.windows(4).enumerate().filter(|(_, w)| *w == b"_S5_").map(|(i, _)| i);
+
+    for pos in names {
+        // Must be a NameOp declaration, not a reference to the name
+        let declared = (pos >= 1 && aml[pos - 1] == AML_NAME_OP)
+            || (pos >= 2 && aml[pos - 1] == AML_ROOT_CHAR && aml[pos - 2] == AML_NAME_OP);
+        if !declared || read_u8(aml, pos + 4) != AML_PACKAGE_OP {
+            continue;
+        }
+
+        // PkgLength: the top two bits count the bytes that follow the first
+        let pkg_length = pos + 5;
+        let pkg_length_len = 1 + (read_u8(aml, pkg_length) >> 6) as usize;
+
+        // Skip NumElements
+        let first = pkg_length + pkg_length_len + 1;
+        let (a, len) = aml_integer(aml, first)?;
+        let (b, _) = aml_integer(aml, first + len)?;
+        return Some(SleepType { a, b });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_s5() {
+        // Name (\_S5, Package (0x04) { 0x05, 0x05, Zero, Zero }) as iasl
+        // emits it, after unrelated AML
+        let aml = [
+            0x10, 0x05, 0x5C, 0x00, 0x00, // Scope (\) { }
+            0x08, 0x5C, b'_', b'S', b'5', b'_', 0x12, 0x0A, 0x04, 0x0A, 0x05, 0x0A, 0x05, 0x00, 0x00,
+        ];
+        assert_eq!(find_s5(&aml), Some(SleepType { a: 5, b: 5 }));
+
+        // Without the root prefix, with Zero/One constants
+        let aml = [0x08, b'_', b'S', b'5', b'_', 0x12, 0x06, 0x04, 0x00, 0x01, 0x00, 0x00];
+        assert_eq!(find_s5(&aml), Some(SleepType { a: 0, b: 1 }));
+
+        // A reference to _S5 is not its declaration
+        let aml = [0x70, b'_', b'S', b'5', b'_', 0x12, 0x06, 0x04, 0x00, 0x01];
+        assert_eq!(find_s5(&aml), None);
+
+        assert_eq!(find_s5(&[]), None);
+    }
+
+    #[test]
+    fn test_pm1_control() {
+        // SCI_EN kept, old SLP_TYP replaced
+        let current = PM1_SCI_EN | (3 << PM1_SLP_TYP_SHIFT);
+        assert_eq!(
+            pm1_control(current, 5),
+            PM1_SCI_EN | (5 << PM1_SLP_TYP_SHIFT) | PM1_SLP_EN
+        );
+    }
+}
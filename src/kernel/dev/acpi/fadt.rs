@@ -0,0 +1,190 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! FADT (Fixed ACPI Description Table)
+//!
+//! Extracts the power management register blocks, SCI interrupt, reset
+//! register and legacy device flags. The FADT signature is "FACP".
+
+use super::tables::{read_u16, read_u32, read_u64, read_u8, GenericAddress, Table};
+
+/// FADT signature
+pub const FADT_SIGNATURE: &[u8; 4] = b"FACP";
+
+/// IA-PC boot architecture flags
+pub const BOOT_ARCH_LEGACY_DEVICES: u16 = 1 << 0;
+pub const BOOT_ARCH_8042: u16 = 1 << 1;
+pub const BOOT_ARCH_VGA_NOT_PRESENT: u16 = 1 << 2;
+
+/// Fixed feature flags
+pub const FLAG_RESET_REG_SUP: u32 = 1 << 10;
+pub const FLAG_HW_REDUCED_ACPI: u32 = 1 << 20;
+pub const FLAG_LOW_POWER_S0_IDLE: u32 = 1 << 21;
+
+/// Parsed FADT
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fadt {
+    /// Physical address of the DSDT (X_DSDT preferred)
+    pub dsdt: u64,
+
+    /// Physical address of the FACS (X_FIRMWARE_CTRL preferred)
+    pub facs: u64,
+
+    /// SCI interrupt (ISA IRQ number)
+    pub sci_irq: u16,
+
+    /// SMI command port (0 if ACPI mode is always on)
+    pub smi_cmd: u32,
+
+    /// Value to write to SMI_CMD to enable ACPI
+    pub acpi_enable: u8,
+
+    /// PM1a control block port
+    pub pm1a_control: u32,
+
+    /// PM1b control block port (0 if absent)
+    pub pm1b_control: u32,
+
+    /// PM timer port (0 if absent)
+    pub pm_timer: u32,
+
+    /// PM timer block length (4 when present)
+    pub pm_timer_len: u8,
+
+    /// Worst-case C2 exit latency in microseconds (> 100 if unsupported)
+    pub c2_latency: u16,
+
+    /// Worst-case C3 exit latency in microseconds (> 1000 if unsupported)
+    pub c3_latency: u16,
+
+    /// RTC century register index (0 if absent)
+    pub century: u8,
+
+    /// IA-PC boot architecture flags
+    pub boot_arch: u16,
+
+    /// Fixed feature flags
+    pub flags: u32,
+
+    /// Reset register (valid if `FLAG_RESET_REG_SUP` is set)
+    pub reset_reg: Option<GenericAddress>,
+
+    /// Value to write to the reset register
+    pub reset_value: u8,
+}
+
+impl Fadt {
+    /// Parse a validated FADT
+    ///
+    /// Fields beyond the end of older (shorter) FADT revisions read as 0.
+    pub fn parse(table: &Table) -> Self {
+        let b = table.bytes();
+
+        let dsdt32 = read_u32(b, 40) as u64;
+        let x_dsdt = read_u64(b, 140);
+        let facs32 = read_u32(b, 36) as u64;
+        let x_facs = read_u64(b, 132);
+        let flags = read_u32(b, 112);
+
+        let reset_reg = if flags & FLAG_RESET_REG_SUP != 0 && b.len() >= 129 {
+            let gas = GenericAddress::parse(b, 116);
+            if gas.address != 0 {
+                Some(gas)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        Self {
+            dsdt: if x_dsdt != 0 { x_dsdt } else { dsdt32 },
+            facs: if x_facs != 0 { x_facs } else { facs32 },
+            sci_irq: read_u16(b, 46),
+            smi_cmd: read_u32(b, 48),
+            acpi_enable: read_u8(b, 52),
+            pm1a_control: read_u32(b, 64),
+            pm1b_control: read_u32(b, 68),
+            pm_timer: read_u32(b, 76),
+            pm_timer_len: read_u8(b, 91),
+            c2_latency: read_u16(b, 96),
+            c3_latency: read_u16(b, 98),
+            century: read_u8(b, 108),
+            boot_arch: read_u16(b, 109),
+            flags,
+            reset_reg,
+            reset_value: read_u8(b, 128),
+        }
+    }
+
+    /// Hardware-reduced ACPI platform (no fixed hardware, no SCI_EN)
+    pub fn hw_reduced(&self) -> bool {
+        self.flags & FLAG_HW_REDUCED_ACPI != 0
+    }
+
+    /// An i8042 keyboard controller is present
+    pub fn has_8042(&self) -> bool {
+        self.boot_arch & BOOT_ARCH_8042 != 0
+    }
+
+    /// C2 exit latency in nanoseconds, if the platform supports C2
+    pub fn c2_latency_ns(&self) -> Option<u64> {
+        (self.c2_latency <= 100).then(|| self.c2_latency as u64 * 1000)
+    }
+
+    /// C3 exit latency in nanoseconds, if the platform supports C3
+    pub fn c3_latency_ns(&self) -> Option<u64> {
+        (self.c3_latency <= 1000).then(|| self.c3_latency as u64 * 1000)
+    }
+
+    /// The platform supports S0 idle (modern standby)
+    pub fn low_power_s0_idle(&self) -> bool {
+        self.flags & FLAG_LOW_POWER_S0_IDLE != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+    use crate::kernel::dev::acpi::tables::GAS_SYSTEM_IO;
+
+    #[test]
+    fn test_fadt_parse() {
+        let mut buf = [0u8; 244];
+        let len = buf.len();
+        write_header(&mut buf, FADT_SIGNATURE, len);
+        buf[40..44].copy_from_slice(&0x7fe0_1000u32.to_le_bytes());
+        buf[140..148].copy_from_slice(&0x7fe0_2000u64.to_le_bytes());
+        buf[46..48].copy_from_slice(&9u16.to_le_bytes());
+        buf[76..80].copy_from_slice(&0x608u32.to_le_bytes());
+        buf[91] = 4;
+        buf[96..98].copy_from_slice(&20u16.to_le_bytes());
+        buf[98..100].copy_from_slice(&1001u16.to_le_bytes());
+        buf[109..111].copy_from_slice(&BOOT_ARCH_8042.to_le_bytes());
+        buf[112..116].copy_from_slice(&FLAG_RESET_REG_SUP.to_le_bytes());
+        buf[116] = GAS_SYSTEM_IO;
+        buf[117] = 8;
+        buf[120..128].copy_from_slice(&0xcf9u64.to_le_bytes());
+        buf[128] = 0x06;
+        fix_checksum(&mut buf, 9);
+
+        let table = Table::parse(&buf).unwrap();
+        let fadt = Fadt::parse(&table);
+        assert_eq!(fadt.dsdt, 0x7fe0_2000);
+        assert_eq!(fadt.sci_irq, 9);
+        assert_eq!(fadt.pm_timer, 0x608);
+        assert!(fadt.has_8042());
+        assert!(!fadt.hw_reduced());
+        assert_eq!(fadt.c2_latency_ns(), Some(20_000));
+        assert_eq!(fadt.c3_latency_ns(), None);
+
+        let reset = fadt.reset_reg.unwrap();
+        assert_eq!(reset.space_id, GAS_SYSTEM_IO);
+        assert_eq!(reset.address, 0xcf9);
+        assert_eq!(fadt.reset_value, 0x06);
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! GTDT (Generic Timer Description Table)
+//!
+//! Describes the Arm generic timer interrupts and the platform timers. The
+//! table signature is "GTDT".
+//!
+//! Only the SBSA generic watchdogs are kept; GT blocks are skipped, since
+//! the kernel uses the per-CPU timer.
+
+use super::tables::{read_u16, read_u32, read_u64, read_u8, Table};
+
+/// GTDT signature
+pub const GTDT_SIGNATURE: &[u8; 4] = b"GTDT";
+
+/// Maximum watchdogs recorded
+pub const MAX_GTDT_WATCHDOGS: usize = 2;
+
+/// Platform timer structure types
+const PLATFORM_TIMER_SBSA_WATCHDOG: u8 = 1;
+
+/// Length of an SBSA watchdog structure
+const SBSA_WATCHDOG_LEN: usize = 28;
+
+/// Watchdog flag: the watchdog is only accessible from the secure world
+const WATCHDOG_SECURE: u32 = 1 << 2;
+
+/// An SBSA generic watchdog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SbsaWatchdog {
+    /// Physical base of the refresh frame
+    pub refresh_base: u64,
+
+    /// Physical base of the control frame
+    pub control_base: u64,
+
+    /// Interrupt of the first stage (WS0)
+    pub gsiv: u32,
+
+    /// Interrupt flags
+    pub flags: u32,
+}
+
+impl SbsaWatchdog {
+    const EMPTY: Self = Self { refresh_base: 0, control_base: 0, gsiv: 0, flags: 0 };
+}
+
+/// Parsed GTDT
+#[derive(Debug, Clone, Copy)]
+pub struct Gtdt {
+    watchdogs: [SbsaWatchdog; MAX_GTDT_WATCHDOGS],
+    watchdog_count: usize,
+}
+
+impl Gtdt {
+    /// Empty GTDT
+    pub const fn new() -> Self {
+        Self { watchdogs: [SbsaWatchdog::EMPTY; MAX_GTDT_WATCHDOGS], watchdog_count: 0 }
+    }
+
+    /// Parse a validated GTDT
+    pub fn parse(table: &Table) -> Self {
+        let bytes = table.bytes();
+        let mut gtdt = Self::new();
+        let count = read_u32(bytes, 88) as usize;
+        let mut off = read_u32(bytes, 92) as usize;
+
+        for _ in 0..count {
+            let len = read_u16(bytes, off + 1) as usize;
+            if len < 4 || off + len > bytes.len() {
+                break;
+            }
+            let timer = &bytes[off..off + len];
+            off += len;
+
+            if read_u8(timer, 0) != PLATFORM_TIMER_SBSA_WATCHDOG || len < SBSA_WATCHDOG_LEN {
+                continue;
+            }
+            let flags = read_u32(timer, 24);
+            if flags & WATCHDOG_SECURE != 0 || gtdt.watchdog_count == MAX_GTDT_WATCHDOGS {
+                continue;
+            }
+            gtdt.watchdogs[gtdt.watchdog_count] = SbsaWatchdog {
+                refresh_base: read_u64(timer, 4),
+                control_base: read_u64(timer, 12),
+                gsiv: read_u32(timer, 20),
+                flags,
+            };
+            gtdt.watchdog_count += 1;
+        }
+
+        gtdt
+    }
+
+    /// Non-secure SBSA watchdogs
+    pub fn watchdogs(&self) -> &[SbsaWatchdog] {
+        &self.watchdogs[..self.watchdog_count]
+    }
+}
+
+impl Default for Gtdt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_gtdt_parse() {
+        let mut buf = [0u8; 160];
+        let first = 96;
+        buf[88..92].copy_from_slice(&2u32.to_le_bytes());
+        buf[92..96].copy_from_slice(&(first as u32).to_le_bytes());
+
+        // Non-secure watchdog
+        let wd = first;
+        buf[wd] = PLATFORM_TIMER_SBSA_WATCHDOG;
+        buf[wd + 1] = SBSA_WATCHDOG_LEN as u8;
+        buf[wd + 4..wd + 12].copy_from_slice(&0x5001_0000u64.to_le_bytes());
+        buf[wd + 12..wd + 20].copy_from_slice(&0x5001_1000u64.to_le_bytes());
+        buf[wd + 20..wd + 24].copy_from_slice(&48u32.to_le_bytes());
+
+        // Secure watchdog, skipped
+        let secure = wd + SBSA_WATCHDOG_LEN;
+        buf[secure] = PLATFORM_TIMER_SBSA_WATCHDOG;
+        buf[secure + 1] = SBSA_WATCHDOG_LEN as u8;
+        buf[secure + 24..secure + 28].copy_from_slice(&WATCHDOG_SECURE.to_le_bytes());
+        let len = secure + SBSA_WATCHDOG_LEN;
+
+        write_header(&mut buf, GTDT_SIGNATURE, len);
+        fix_checksum(&mut buf[..len], 9);
+
+        let gtdt = Gtdt::parse(&Table::parse(&buf[..len]).unwrap());
+        assert_eq!(gtdt.watchdogs().len(), 1);
+        assert_eq!(gtdt.watchdogs()[0].refresh_base, 0x5001_0000);
+        assert_eq!(gtdt.watchdogs()[0].control_base, 0x5001_1000);
+        assert_eq!(gtdt.watchdogs()[0].gsiv, 48);
+    }
+}
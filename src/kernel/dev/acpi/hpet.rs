@@ -0,0 +1,88 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! HPET (High Precision Event Timer) Table
+//!
+//! Locates the HPET register block. The table signature is "HPET".
+
+use super::tables::{read_u16, read_u32, read_u8, GenericAddress, Table, GAS_SYSTEM_MEMORY};
+
+/// HPET signature
+pub const HPET_SIGNATURE: &[u8; 4] = b"HPET";
+
+/// Parsed HPET description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hpet {
+    /// Physical MMIO base of the register block
+    pub base: u64,
+
+    /// Event timer block ID (hardware revision, comparator count, vendor)
+    pub block_id: u32,
+
+    /// HPET sequence number
+    pub number: u8,
+
+    /// Minimum clock ticks for periodic mode without lost interrupts
+    pub min_tick: u16,
+}
+
+impl Hpet {
+    /// Parse a validated HPET table
+    ///
+    /// Returns None if the register block is not memory mapped.
+    pub fn parse(table: &Table) -> Option<Self> {
+        let b = table.bytes();
+        if b.len() < 56 {
+            return None;
+        }
+
+        let gas = GenericAddress::parse(b, 40);
+        if gas.space_id != GAS_SYSTEM_MEMORY || gas.address == 0 {
+            return None;
+        }
+
+        Some(Self {
+            base: gas.address,
+            block_id: read_u32(b, 36),
+            number: read_u8(b, 52),
+            min_tick: read_u16(b, 53),
+        })
+    }
+
+    /// Number of comparators (timers) in the block
+    pub fn comparator_count(&self) -> u32 {
+        ((self.block_id >> 8) & 0x1f) + 1
+    }
+
+    /// PCI vendor ID of the HPET implementation
+    pub fn vendor_id(&self) -> u16 {
+        (self.block_id >> 16) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_hpet_parse() {
+        let mut buf = [0u8; 56];
+        write_header(&mut buf, HPET_SIGNATURE, 56);
+        buf[36..40].copy_from_slice(&0x8086_a201u32.to_le_bytes());
+        buf[40] = GAS_SYSTEM_MEMORY;
+        buf[44..52].copy_from_slice(&0xfed0_0000u64.to_le_bytes());
+        buf[53..55].copy_from_slice(&0x80u16.to_le_bytes());
+        fix_checksum(&mut buf, 9);
+
+        let table = Table::parse(&buf).unwrap();
+        let hpet = Hpet::parse(&table).unwrap();
+        assert_eq!(hpet.base, 0xfed0_0000);
+        assert_eq!(hpet.comparator_count(), 3);
+        assert_eq!(hpet.vendor_id(), 0x8086);
+        assert_eq!(hpet.min_tick, 0x80);
+    }
+}
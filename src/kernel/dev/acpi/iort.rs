@@ -0,0 +1,223 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! IORT (I/O Remapping Table)
+//!
+//! Describes the Arm SMMUv3 instances and how PCI requester IDs of each
+//! root complex map to SMMU stream IDs. The table signature is "IORT".
+//!
+//! Only root complex mappings that lead straight to an SMMUv3 are kept;
+//! mappings to ITS groups (MSI routing) and named components are
+//! skipped.
+
+use super::tables::{read_u16, read_u32, read_u64, read_u8, Table, SDT_HEADER_LEN};
+
+/// IORT signature
+pub const IORT_SIGNATURE: &[u8; 4] = b"IORT";
+
+/// Maximum SMMUs recorded
+pub const MAX_IORT_SMMUS: usize = 4;
+
+/// Maximum root complex ID mappings recorded
+pub const MAX_IORT_MAPPINGS: usize = 16;
+
+/// Node types
+const NODE_ROOT_COMPLEX: u8 = 2;
+const NODE_SMMU_V3: u8 = 4;
+
+/// Size of one ID mapping
+const ID_MAPPING_LEN: usize = 20;
+
+/// ID mapping flags
+const MAPPING_SINGLE: u32 = 1 << 0;
+
+/// An SMMUv3 instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IortSmmu {
+    /// Physical base of the register pages
+    pub base: u64,
+
+    /// Offset of the node in the table, which mappings refer to it by
+    node_offset: u32,
+}
+
+impl IortSmmu {
+    const EMPTY: Self = Self { base: 0, node_offset: 0 };
+}
+
+/// A range of requester IDs of a root complex and the stream IDs they
+/// reach an SMMU with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IortMapping {
+    /// PCI segment of the root complex
+    pub segment: u32,
+
+    /// First requester ID
+    pub input_base: u32,
+
+    /// Number of requester IDs
+    pub count: u32,
+
+    /// Stream ID of the first requester ID
+    pub output_base: u32,
+
+    /// Index of the SMMU in [`Iort::smmus`]
+    pub smmu: usize,
+}
+
+impl IortMapping {
+    const EMPTY: Self = Self { segment: 0, input_base: 0, count: 0, output_base: 0, smmu: 0 };
+}
+
+/// Parsed IORT
+#[derive(Debug, Clone, Copy)]
+pub struct Iort {
+    smmus: [IortSmmu; MAX_IORT_SMMUS],
+    smmu_count: usize,
+
+    mappings: [IortMapping; MAX_IORT_MAPPINGS],
+    mapping_count: usize,
+}
+
+impl Iort {
+    /// Empty IORT
+    pub const fn new() -> Self {
+        Self {
+            smmus: [IortSmmu::EMPTY; MAX_IORT_SMMUS],
+            smmu_count: 0,
+            mappings: [IortMapping::EMPTY; MAX_IORT_MAPPINGS],
+            mapping_count: 0,
+        }
+    }
+
+    /// Parse a validated IORT
+    pub fn parse(table: &Table) -> Self {
+        let bytes = table.bytes();
+        let mut iort = Self::new();
+        let node_count = read_u32(bytes, SDT_HEADER_LEN) as usize;
+        let first = read_u32(bytes, SDT_HEADER_LEN + 4) as usize;
+
+        // SMMUs first, since root complexes refer to them by offset
+        for (off, node) in Self::nodes(bytes, first, node_count) {
+            if read_u8(node, 0) == NODE_SMMU_V3 && node.len() >= 24 && iort.smmu_count < MAX_IORT_SMMUS {
+                iort.smmus[iort.smmu_count] = IortSmmu { base: read_u64(node, 16), node_offset: off as u32 };
+                iort.smmu_count += 1;
+            }
+        }
+
+        for (_, node) in Self::nodes(bytes, first, node_count) {
+            if read_u8(node, 0) != NODE_ROOT_COMPLEX || node.len() < 32 {
+                continue;
+            }
+            let segment = read_u32(node, 28);
+            let mapping_count = read_u32(node, 8) as usize;
+            let mut off = read_u32(node, 12) as usize;
+
+            for _ in 0..mapping_count {
+                if off + ID_MAPPING_LEN > node.len() || iort.mapping_count == MAX_IORT_MAPPINGS {
+                    break;
+                }
+                let target = read_u32(node, off + 12);
+                if let Some(smmu) = iort.smmus().iter().position(|s| s.node_offset == target) {
+                    let single = read_u32(node, off + 16) & MAPPING_SINGLE != 0;
+                    iort.mappings[iort.mapping_count] = IortMapping {
+                        segment,
+                        input_base: read_u32(node, off),
+                        // The field holds the count minus one
+                        count: if single { 1 } else { read_u32(node, off + 4).saturating_add(1) },
+                        output_base: read_u32(node, off + 8),
+                        smmu,
+                    };
+                    iort.mapping_count += 1;
+                }
+                off += ID_MAPPING_LEN;
+            }
+        }
+
+        iort
+    }
+
+    /// Offset and bytes of each well-formed node
+    fn nodes(bytes: &[u8], first: usize, count: usize) -> impl Iterator<Item = (usize, &[u8])> {
+        let mut off = first;
+        (0..count).map_while(move |_| {
+            let len = read_u16(bytes, off + 1) as usize;
+            if len < 16 || off + len > bytes.len() {
+                return None;
+            }
+            let node = (off, &bytes[off..off + len]);
+            off += len;
+            Some(node)
+        })
+    }
+
+    /// All SMMUv3 instances
+    pub fn smmus(&self) -> &[IortSmmu] {
+        &self.smmus[..self.smmu_count]
+    }
+
+    /// Root complex mappings to an SMMU
+    pub fn mappings(&self) -> &[IortMapping] {
+        &self.mappings[..self.mapping_count]
+    }
+
+    /// SMMU index and stream ID for requester `rid` on `segment`
+    pub fn stream_id(&self, segment: u32, rid: u16) -> Option<(usize, u32)> {
+        let rid = rid as u32;
+        self.mappings()
+            .iter()
+            .find(|m| m.segment == segment && rid >= m.input_base && rid - m.input_base < m.count)
+            .map(|m| (m.smmu, m.output_base + (rid - m.input_base)))
+    }
+}
+
+impl Default for Iort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_iort_parse() {
+        let mut buf = [0u8; 160];
+        let first = SDT_HEADER_LEN + 12;
+        buf[SDT_HEADER_LEN..SDT_HEADER_LEN + 4].copy_from_slice(&2u32.to_le_bytes());
+        buf[SDT_HEADER_LEN + 4..SDT_HEADER_LEN + 8].copy_from_slice(&(first as u32).to_le_bytes());
+
+        // SMMUv3 node
+        let smmu = first;
+        buf[smmu] = NODE_SMMU_V3;
+        buf[smmu + 1] = 44;
+        buf[smmu + 16..smmu + 24].copy_from_slice(&0x0900_0000u64.to_le_bytes());
+
+        // Root complex on segment 0: RIDs 0-0xffff to stream IDs 0x10000+
+        let rc = smmu + 44;
+        buf[rc] = NODE_ROOT_COMPLEX;
+        buf[rc + 1] = 36 + ID_MAPPING_LEN as u8;
+        buf[rc + 8] = 1;
+        buf[rc + 12] = 36;
+        let map = rc + 36;
+        buf[map + 4..map + 8].copy_from_slice(&0xffffu32.to_le_bytes());
+        buf[map + 8..map + 12].copy_from_slice(&0x10000u32.to_le_bytes());
+        buf[map + 12..map + 16].copy_from_slice(&(smmu as u32).to_le_bytes());
+        let len = map + ID_MAPPING_LEN;
+
+        write_header(&mut buf, IORT_SIGNATURE, len);
+        fix_checksum(&mut buf[..len], 9);
+
+        let iort = Iort::parse(&Table::parse(&buf[..len]).unwrap());
+        assert_eq!(iort.smmus().len(), 1);
+        assert_eq!(iort.smmus()[0].base, 0x0900_0000);
+        assert_eq!(iort.mappings().len(), 1);
+        assert_eq!(iort.stream_id(0, 0x0108), Some((0, 0x10108)));
+        assert_eq!(iort.stream_id(1, 0x0108), None);
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! MADT (Multiple APIC Description Table)
+//!
+//! Enumerates local APICs (CPUs), I/O APICs and ISA interrupt source
+//! overrides. The MADT signature is "APIC".
+
+use super::tables::{read_u16, read_u32, read_u64, read_u8, Table, SDT_HEADER_LEN};
+use crate::kernel::percpu::SMP_MAX_CPUS;
+
+/// MADT signature
+pub const MADT_SIGNATURE: &[u8; 4] = b"APIC";
+
+/// Maximum I/O APICs recorded
+pub const MAX_IOAPICS: usize = 16;
+
+/// Maximum interrupt source overrides recorded
+pub const MAX_OVERRIDES: usize = 32;
+
+/// MADT flags: dual 8259 PICs installed
+const MADT_FLAG_PCAT_COMPAT: u32 = 1 << 0;
+
+/// Entry types
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_INTERRUPT_OVERRIDE: u8 = 2;
+const ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+const ENTRY_LOCAL_X2APIC: u8 = 9;
+
+/// Local APIC flags
+const LAPIC_ENABLED: u32 = 1 << 0;
+const LAPIC_ONLINE_CAPABLE: u32 = 1 << 1;
+
+/// A processor described by a local APIC / x2APIC entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalApic {
+    /// ACPI processor UID
+    pub processor_uid: u32,
+
+    /// APIC ID (x2APIC ID for x2APIC entries)
+    pub apic_id: u32,
+
+    /// Processor is enabled
+    pub enabled: bool,
+
+    /// Processor can be brought online later (hotplug)
+    pub online_capable: bool,
+}
+
+impl LocalApic {
+    const EMPTY: Self = Self {
+        processor_uid: 0,
+        apic_id: 0,
+        enabled: false,
+        online_capable: false,
+    };
+}
+
+/// An I/O APIC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApic {
+    /// I/O APIC ID
+    pub id: u8,
+
+    /// Physical MMIO address
+    pub address: u32,
+
+    /// First global system interrupt handled by this I/O APIC
+    pub gsi_base: u32,
+}
+
+impl IoApic {
+    const EMPTY: Self = Self { id: 0, address: 0, gsi_base: 0 };
+}
+
+/// ISA IRQ to GSI override
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptOverride {
+    /// Bus (always 0 = ISA)
+    pub bus: u8,
+
+    /// ISA IRQ number
+    pub source: u8,
+
+    /// Global system interrupt
+    pub gsi: u32,
+
+    /// MPS INTI flags (polarity, trigger mode)
+    pub flags: u16,
+}
+
+impl InterruptOverride {
+    const EMPTY: Self = Self { bus: 0, source: 0, gsi: 0, flags: 0 };
+
+    /// Polarity is active low
+    pub fn active_low(&self) -> bool {
+        self.flags & 0x3 == 0x3
+    }
+
+    /// Trigger mode is level
+    pub fn level_triggered(&self) -> bool {
+        (self.flags >> 2) & 0x3 == 0x3
+    }
+}
+
+/// Parsed MADT
+#[derive(Debug, Clone)]
+pub struct Madt {
+    /// Physical address of the local APIC MMIO window
+    pub local_apic_address: u64,
+
+    /// Legacy 8259 PICs present and must be masked
+    pub pcat_compat: bool,
+
+    cpus: [LocalApic; SMP_MAX_CPUS],
+    cpu_count: usize,
+
+    ioapics: [IoApic; MAX_IOAPICS],
+    ioapic_count: usize,
+
+    overrides: [InterruptOverride; MAX_OVERRIDES],
+    override_count: usize,
+}
+
+impl Madt {
+    /// Empty MADT
+    pub const fn new() -> Self {
+        Self {
+            local_apic_address: 0,
+            pcat_compat: false,
+            cpus: [LocalApic::EMPTY; SMP_MAX_CPUS],
+            cpu_count: 0,
+            ioapics: [IoApic::EMPTY; MAX_IOAPICS],
+            ioapic_count: 0,
+            overrides: [InterruptOverride::EMPTY; MAX_OVERRIDES],
+            override_count: 0,
+        }
+    }
+
+    /// Parse a validated MADT
+    pub fn parse(table: &Table) -> Self {
+        let bytes = table.bytes();
+        let mut madt = Self::new();
+
+        madt.local_apic_address = read_u32(bytes, SDT_HEADER_LEN) as u64;
+        madt.pcat_compat = read_u32(bytes, SDT_HEADER_LEN + 4) & MADT_FLAG_PCAT_COMPAT != 0;
+
+        let mut off = SDT_HEADER_LEN + 8;
+        while off + 2 <= bytes.len() {
+            let entry_type = read_u8(bytes, off);
+            let len = read_u8(bytes, off + 1) as usize;
+            if len < 2 || off + len > bytes.len() {
+                break;
+            }
+            let entry = &bytes[off..off + len];
+
+            match entry_type {
+                ENTRY_LOCAL_APIC if len >= 8 => {
+                    let flags = read_u32(entry, 4);
+                    madt.push_cpu(LocalApic {
+                        processor_uid: read_u8(entry, 2) as u32,
+                        apic_id: read_u8(entry, 3) as u32,
+                        enabled: flags & LAPIC_ENABLED != 0,
+                        online_capable: flags & LAPIC_ONLINE_CAPABLE != 0,
+                    });
+                }
+                ENTRY_LOCAL_X2APIC if len >= 16 => {
+                    let flags = read_u32(entry, 8);
+                    madt.push_cpu(LocalApic {
+                        processor_uid: read_u32(entry, 12),
+                        apic_id: read_u32(entry, 4),
+                        enabled: flags & LAPIC_ENABLED != 0,
+                        online_capable: flags & LAPIC_ONLINE_CAPABLE != 0,
+                    });
+                }
+                ENTRY_IO_APIC if len >= 12 => {
+                    if madt.ioapic_count < MAX_IOAPICS {
+                        madt.ioapics[madt.ioapic_count] = IoApic {
+                            id: read_u8(entry, 2),
+                            address: read_u32(entry, 4),
+                            gsi_base: read_u32(entry, 8),
+                        };
+                        madt.ioapic_count += 1;
+                    }
+                }
+                ENTRY_INTERRUPT_OVERRIDE if len >= 10 => {
+                    if madt.override_count < MAX_OVERRIDES {
+                        madt.overrides[madt.override_count] = InterruptOverride {
+                            bus: read_u8(entry, 2),
+                            source: read_u8(entry, 3),
+                            gsi: read_u32(entry, 4),
+                            flags: read_u16(entry, 8),
+                        };
+                        madt.override_count += 1;
+                    }
+                }
+                ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE if len >= 12 => {
+                    madt.local_apic_address = read_u64(entry, 4);
+                }
+                _ => {}
+            }
+
+            off += len;
+        }
+
+        madt
+    }
+
+    fn push_cpu(&mut self, cpu: LocalApic) {
+        // Entries that are neither enabled nor hotpluggable are unusable
+        if !cpu.enabled && !cpu.online_capable {
+            return;
+        }
+        if self.cpu_count < SMP_MAX_CPUS {
+            self.cpus[self.cpu_count] = cpu;
+            self.cpu_count += 1;
+        }
+    }
+
+    /// All usable processors
+    pub fn cpus(&self) -> &[LocalApic] {
+        &self.cpus[..self.cpu_count]
+    }
+
+    /// Enabled processors
+    pub fn enabled_cpus(&self) -> impl Iterator<Item = &LocalApic> {
+        self.cpus().iter().filter(|c| c.enabled)
+    }
+
+    /// All I/O APICs
+    pub fn ioapics(&self) -> &[IoApic] {
+        &self.ioapics[..self.ioapic_count]
+    }
+
+    /// All interrupt source overrides
+    pub fn overrides(&self) -> &[InterruptOverride] {
+        &self.overrides[..self.override_count]
+    }
+
+    /// Translate an ISA IRQ to its global system interrupt
+    pub fn isa_irq_to_gsi(&self, irq: u8) -> u32 {
+        self.overrides()
+            .iter()
+            .find(|o| o.bus == 0 && o.source == irq)
+            .map(|o| o.gsi)
+            .unwrap_or(irq as u32)
+    }
+}
+
+impl Default for Madt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    fn build_madt(buf: &mut [u8]) -> usize {
+        let mut off = SDT_HEADER_LEN;
+        buf[off..off + 4].copy_from_slice(&0xfee0_0000u32.to_le_bytes());
+        buf[off + 4..off + 8].copy_from_slice(&MADT_FLAG_PCAT_COMPAT.to_le_bytes());
+        off += 8;
+
+        // Two enabled CPUs and one disabled, non-hotpluggable CPU
+        for (uid, apic_id, flags) in [(0u8, 0u8, 1u32), (1, 2, 1), (2, 4, 0)] {
+            buf[off] = ENTRY_LOCAL_APIC;
+            buf[off + 1] = 8;
+            buf[off + 2] = uid;
+            buf[off + 3] = apic_id;
+            buf[off + 4..off + 8].copy_from_slice(&flags.to_le_bytes());
+            off += 8;
+        }
+
+        buf[off] = ENTRY_IO_APIC;
+        buf[off + 1] = 12;
+        buf[off + 2] = 8;
+        buf[off + 4..off + 8].copy_from_slice(&0xfec0_0000u32.to_le_bytes());
+        off += 12;
+
+        buf[off] = ENTRY_INTERRUPT_OVERRIDE;
+        buf[off + 1] = 10;
+        buf[off + 3] = 0;
+        buf[off + 4..off + 8].copy_from_slice(&2u32.to_le_bytes());
+        off += 10;
+
+        write_header(buf, MADT_SIGNATURE, off);
+        fix_checksum(&mut buf[..off], 9);
+        off
+    }
+
+    #[test]
+    fn test_madt_parse() {
+        let mut buf = [0u8; 256];
+        let len = build_madt(&mut buf);
+        let table = Table::parse(&buf[..len]).unwrap();
+        let madt = Madt::parse(&table);
+
+        assert_eq!(madt.local_apic_address, 0xfee0_0000);
+        assert!(madt.pcat_compat);
+        assert_eq!(madt.cpus().len(), 2);
+        assert_eq!(madt.cpus()[1].apic_id, 2);
+        assert_eq!(madt.ioapics().len(), 1);
+        assert_eq!(madt.ioapics()[0].address, 0xfec0_0000);
+        assert_eq!(madt.isa_irq_to_gsi(0), 2);
+        assert_eq!(madt.isa_irq_to_gsi(1), 1);
+    }
+
+    #[test]
+    fn test_madt_truncated_entry() {
+        let mut buf = [0u8; 64];
+        let mut off = SDT_HEADER_LEN + 8;
+        buf[off] = ENTRY_LOCAL_APIC;
+        buf[off + 1] = 200; // runs past the end of the table
+        off += 8;
+        write_header(&mut buf, MADT_SIGNATURE, off);
+        fix_checksum(&mut buf[..off], 9);
+
+        let table = Table::parse(&buf[..off]).unwrap();
+        assert!(Madt::parse(&table).cpus().is_empty());
+    }
+}
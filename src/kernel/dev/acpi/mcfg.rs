@@ -0,0 +1,118 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! MCFG (PCI Express Memory-mapped Configuration) Table
+//!
+//! Lists the ECAM windows for each PCI segment group. The table signature
+//! is "MCFG".
+
+use super::tables::{read_u16, read_u64, read_u8, Table, SDT_HEADER_LEN};
+
+/// MCFG signature
+pub const MCFG_SIGNATURE: &[u8; 4] = b"MCFG";
+
+/// Maximum ECAM windows recorded
+pub const MAX_MCFG_ENTRIES: usize = 8;
+
+/// Size of one allocation entry
+const ENTRY_LEN: usize = 16;
+
+/// One ECAM window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McfgEntry {
+    /// Physical base of the ECAM window (for bus 0 of the segment)
+    pub base: u64,
+
+    /// PCI segment group
+    pub segment: u16,
+
+    /// First decoded bus
+    pub bus_start: u8,
+
+    /// Last decoded bus
+    pub bus_end: u8,
+}
+
+impl McfgEntry {
+    const EMPTY: Self = Self { base: 0, segment: 0, bus_start: 0, bus_end: 0 };
+}
+
+/// Parsed MCFG
+#[derive(Debug, Clone, Copy)]
+pub struct Mcfg {
+    entries: [McfgEntry; MAX_MCFG_ENTRIES],
+    count: usize,
+}
+
+impl Mcfg {
+    /// Empty MCFG
+    pub const fn new() -> Self {
+        Self { entries: [McfgEntry::EMPTY; MAX_MCFG_ENTRIES], count: 0 }
+    }
+
+    /// Parse a validated MCFG
+    pub fn parse(table: &Table) -> Self {
+        let b = table.bytes();
+        let mut mcfg = Self::new();
+
+        // 8 reserved bytes follow the header
+        let mut off = SDT_HEADER_LEN + 8;
+        while off + ENTRY_LEN <= b.len() && mcfg.count < MAX_MCFG_ENTRIES {
+            let entry = McfgEntry {
+                base: read_u64(b, off),
+                segment: read_u16(b, off + 8),
+                bus_start: read_u8(b, off + 10),
+                bus_end: read_u8(b, off + 11),
+            };
+            if entry.base != 0 && entry.bus_end >= entry.bus_start {
+                mcfg.entries[mcfg.count] = entry;
+                mcfg.count += 1;
+            }
+            off += ENTRY_LEN;
+        }
+
+        mcfg
+    }
+
+    /// All ECAM windows
+    pub fn entries(&self) -> &[McfgEntry] {
+        &self.entries[..self.count]
+    }
+}
+
+impl Default for Mcfg {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_mcfg_parse() {
+        let mut buf = [0u8; SDT_HEADER_LEN + 8 + ENTRY_LEN * 2];
+        let len = buf.len();
+        write_header(&mut buf, MCFG_SIGNATURE, len);
+
+        let off = SDT_HEADER_LEN + 8;
+        buf[off..off + 8].copy_from_slice(&0xb000_0000u64.to_le_bytes());
+        buf[off + 11] = 0xff;
+        // Second entry has an inverted bus range and is dropped
+        buf[off + 16..off + 24].copy_from_slice(&0xc000_0000u64.to_le_bytes());
+        buf[off + 26] = 4;
+        buf[off + 27] = 2;
+        fix_checksum(&mut buf, 9);
+
+        let table = Table::parse(&buf).unwrap();
+        let mcfg = Mcfg::parse(&table);
+        assert_eq!(mcfg.entries().len(), 1);
+        assert_eq!(mcfg.entries()[0].base, 0xb000_0000);
+        assert_eq!(mcfg.entries()[0].bus_end, 0xff);
+    }
+}
@@ -0,0 +1,458 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ACPI Table Parser
+//!
+//! Walks RSDP → XSDT/RSDT and parses the static tables the kernel needs
+//! during bring-up:
+//! - MADT: CPUs (local APIC / x2APIC), I/O APICs, interrupt overrides
+//! - FADT: PM registers, SCI, reset register, legacy device flags
+//! - DSDT: only the `\_S5` sleep type, for soft-off
+//! - HPET: HPET register block
+//! - MCFG: PCIe ECAM windows
+//! - SRAT: NUMA proximity domains of CPUs and memory
+//! - DMAR: Intel VT-d remapping units
+//! - IORT: Arm SMMUv3 instances and PCI stream ID mappings
+//! - GTDT: Arm SBSA generic watchdogs
+//! - TPM2: TPM 2.0 interface and CRB control area
+//!
+//! # Design
+//!
+//! The RSDP address comes from the boot handoff. Every table is
+//! length-bounded and checksum-validated before it is parsed; tables
+//! that fail validation are skipped with a warning rather than trusted.
+//! Parsed results are kept in a global `AcpiInfo` consumed by SMP
+//! bring-up and the PCIe driver. AML (DSDT/SSDT) is not interpreted.
+//!
+//! # Usage
+//!
+//! ```rust
+//! acpi::init();
+//! let mut ids = [0u32; 256];
+//! let count = acpi::cpu_apic_ids(&mut ids);
+//! ```
+
+pub mod dmar;
+pub mod dsdt;
+pub mod fadt;
+pub mod gtdt;
+pub mod hpet;
+pub mod iort;
+pub mod madt;
+pub mod mcfg;
+pub mod srat;
+pub mod tables;
+pub mod tpm2;
+
+pub use dmar::{DeviceScope, Dmar, DrhdUnit};
+pub use dsdt::SleepType;
+pub use fadt::Fadt;
+pub use gtdt::{Gtdt, SbsaWatchdog};
+pub use hpet::Hpet;
+pub use iort::{Iort, IortMapping, IortSmmu};
+pub use madt::{InterruptOverride, IoApic, LocalApic, Madt};
+pub use mcfg::{Mcfg, McfgEntry};
+pub use srat::{CpuAffinity, MemoryAffinity, Srat};
+pub use tables::{GenericAddress, Rsdp, Table};
+pub use tpm2::Tpm2;
+
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Maximum number of tables remembered from the root table
+pub const MAX_TABLES: usize = 64;
+
+/// A table found in the root table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableRef {
+    /// Table signature
+    pub signature: [u8; 4],
+
+    /// Physical address
+    pub paddr: u64,
+}
+
+impl TableRef {
+    const EMPTY: Self = Self { signature: [0; 4], paddr: 0 };
+}
+
+/// Parsed ACPI state
+pub struct AcpiInfo {
+    /// Root pointer
+    pub rsdp: Option<Rsdp>,
+
+    /// MADT, if present
+    pub madt: Option<Madt>,
+
+    /// FADT, if present
+    pub fadt: Option<Fadt>,
+
+    /// HPET, if present
+    pub hpet: Option<Hpet>,
+
+    /// S5 (soft-off) sleep type from the DSDT, if found
+    pub s5: Option<SleepType>,
+
+    /// ECAM windows (empty if no MCFG)
+    pub mcfg: Mcfg,
+
+    /// NUMA affinity, if present
+    pub srat: Option<Srat>,
+
+    /// VT-d remapping units, if present
+    pub dmar: Option<Dmar>,
+
+    /// SMMUv3 instances and stream IDs, if present
+    pub iort: Option<Iort>,
+
+    /// SBSA watchdogs, if present
+    pub gtdt: Option<Gtdt>,
+
+    /// TPM 2.0 interface, if present
+    pub tpm2: Option<Tpm2>,
+
+    tables: [TableRef; MAX_TABLES],
+    table_count: usize,
+}
+
+impl AcpiInfo {
+    /// Empty state
+    pub const fn new() -> Self {
+        Self {
+            rsdp: None,
+            madt: None,
+            fadt: None,
+            hpet: None,
+            s5: None,
+            mcfg: Mcfg::new(),
+            srat: None,
+            dmar: None,
+            iort: None,
+            gtdt: None,
+            tpm2: None,
+            tables: [TableRef::EMPTY; MAX_TABLES],
+            table_count: 0,
+        }
+    }
+
+    /// All valid tables found in the root table
+    pub fn tables(&self) -> &[TableRef] {
+        &self.tables[..self.table_count]
+    }
+
+    fn record_table(&mut self, signature: [u8; 4], paddr: u64) {
+        if self.table_count < MAX_TABLES {
+            self.tables[self.table_count] = TableRef { signature, paddr };
+            self.table_count += 1;
+        }
+    }
+
+    /// Parse one validated table into the state
+    fn consume(&mut self, table: &Table) {
+        match table.signature() {
+            madt::MADT_SIGNATURE => self.madt = Some(Madt::parse(table)),
+            fadt::FADT_SIGNATURE => {
+                let fadt = Fadt::parse(table);
+                if fadt.dsdt != 0 {
+                    self.record_table(*b"DSDT", fadt.dsdt);
+                }
+                self.fadt = Some(fadt);
+            }
+            hpet::HPET_SIGNATURE => self.hpet = Hpet::parse(table),
+            mcfg::MCFG_SIGNATURE => self.mcfg = Mcfg::parse(table),
+            srat::SRAT_SIGNATURE => self.srat = Some(Srat::parse(table)),
+            dmar::DMAR_SIGNATURE => self.dmar = Some(Dmar::parse(table)),
+            iort::IORT_SIGNATURE => self.iort = Some(Iort::parse(table)),
+            gtdt::GTDT_SIGNATURE => self.gtdt = Some(Gtdt::parse(table)),
+            tpm2::TPM2_SIGNATURE => self.tpm2 = Tpm2::parse(table),
+            _ => {}
+        }
+    }
+}
+
+impl Default for AcpiInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Global ACPI state
+static ACPI: SpinMutex<AcpiInfo> = SpinMutex::new(AcpiInfo::new());
+
+/// Set once tables have been parsed
+static ACPI_READY: AtomicBool = AtomicBool::new(false);
+
+/// ============================================================================
+/// Initialization
+/// ============================================================================
+
+/// Parse ACPI tables using the RSDP from the boot handoff
+pub fn init() {
+    let rsdp = match crate::kernel::handoff::get().and_then(|h| h.acpi_rsdp()) {
+        Some(rsdp) => rsdp,
+        None => {
+            log_info!("ACPI: no RSDP from boot loader");
+            return;
+        }
+    };
+
+    if let Err(err) = unsafe { init_from_rsdp(rsdp) } {
+        log_warn!("ACPI: initialization failed: {}", err);
+    }
+}
+
+/// Parse ACPI tables starting at the RSDP at `rsdp_paddr`
+///
+/// # Safety
+///
+/// `rsdp_paddr` and every table it references must be readable.
+pub unsafe fn init_from_rsdp(rsdp_paddr: u64) -> Result<()> {
+    let rsdp = tables::map_rsdp(rsdp_paddr)?;
+
+    // Prefer the XSDT (64-bit entries) when available
+    let (root_paddr, entry_size) = if rsdp.xsdt_address != 0 {
+        (rsdp.xsdt_address, 8)
+    } else {
+        (rsdp.rsdt_address as u64, 4)
+    };
+
+    let root = tables::map_table(root_paddr)?;
+    if root.signature() != b"XSDT" && root.signature() != b"RSDT" {
+        return Err(RX_ERR_NOT_FOUND);
+    }
+
+    let mut info = AcpiInfo::new();
+    info.rsdp = Some(rsdp);
+
+    for paddr in root.root_entries(entry_size) {
+        match tables::map_table(paddr) {
+            Ok(table) => {
+                info.record_table(*table.signature(), paddr);
+                info.consume(&table);
+            }
+            Err(err) => {
+                log_warn!("ACPI: skipping invalid table at {:#x} ({})", paddr, err);
+            }
+        }
+    }
+
+    if let Some(dsdt) = info.fadt.map(|fadt| fadt.dsdt).filter(|&dsdt| dsdt != 0) {
+        match tables::map_table(dsdt) {
+            Ok(table) => info.s5 = dsdt::find_s5(&table.bytes()[tables::SDT_HEADER_LEN..]),
+            Err(err) => {
+                log_warn!("ACPI: invalid DSDT at {:#x} ({})", dsdt, err);
+            }
+        }
+    }
+
+    log_summary(&info);
+
+    *ACPI.lock() = info;
+    ACPI_READY.store(true, Ordering::Release);
+    Ok(())
+}
+
+fn log_summary(info: &AcpiInfo) {
+    log_info!("ACPI: {} tables", info.table_count);
+    for table in info.tables() {
+        let sig = core::str::from_utf8(&table.signature).unwrap_or("????");
+        log_info!("  {} at {:#x}", sig, table.paddr);
+    }
+
+    if let Some(madt) = &info.madt {
+        log_info!(
+            "ACPI: {} CPUs, {} I/O APICs, LAPIC at {:#x}",
+            madt.cpus().len(),
+            madt.ioapics().len(),
+            madt.local_apic_address
+        );
+    }
+    if let Some(hpet) = &info.hpet {
+        log_info!("ACPI: HPET at {:#x}", hpet.base);
+    }
+    if let Some(s5) = &info.s5 {
+        log_info!("ACPI: S5 sleep type {}/{}", s5.a, s5.b);
+    }
+    if let Some(srat) = &info.srat {
+        log_info!("ACPI: SRAT with {} CPUs, {} memory ranges", srat.cpus().len(), srat.memory().len());
+    }
+    if let Some(dmar) = &info.dmar {
+        log_info!("ACPI: DMAR with {} remapping units", dmar.units().len());
+    }
+    if let Some(iort) = &info.iort {
+        log_info!("ACPI: IORT with {} SMMUv3, {} stream mappings", iort.smmus().len(), iort.mappings().len());
+    }
+    if let Some(gtdt) = &info.gtdt {
+        log_info!("ACPI: GTDT with {} SBSA watchdogs", gtdt.watchdogs().len());
+    }
+    if let Some(tpm2) = &info.tpm2 {
+        log_info!("ACPI: TPM2 start method {}", tpm2.start_method);
+    }
+    for entry in info.mcfg.entries() {
+        log_info!(
+            "ACPI: ECAM segment {} buses {}-{} at {:#x}",
+            entry.segment,
+            entry.bus_start,
+            entry.bus_end,
+            entry.base
+        );
+    }
+}
+
+/// ============================================================================
+/// Queries
+/// ============================================================================
+
+/// Check whether ACPI tables were parsed
+pub fn is_available() -> bool {
+    ACPI_READY.load(Ordering::Acquire)
+}
+
+/// Run `f` with the parsed ACPI state
+///
+/// Returns None if ACPI has not been initialized.
+pub fn with_info<R>(f: impl FnOnce(&AcpiInfo) -> R) -> Option<R> {
+    if !is_available() {
+        return None;
+    }
+    Some(f(&ACPI.lock()))
+}
+
+/// Find a table by signature, returning its physical address
+pub fn find_table(signature: &[u8; 4]) -> Option<u64> {
+    with_info(|info| {
+        info.tables()
+            .iter()
+            .find(|t| &t.signature == signature)
+            .map(|t| t.paddr)
+    })
+    .flatten()
+}
+
+/// Collect the APIC IDs of all enabled CPUs, in MADT order
+///
+/// Firmware lists the boot processor first. Returns the number of IDs
+/// written to `out`.
+pub fn cpu_apic_ids(out: &mut [u32]) -> usize {
+    with_info(|info| {
+        let mut count = 0;
+        if let Some(madt) = &info.madt {
+            for cpu in madt.enabled_cpus() {
+                if count == out.len() {
+                    break;
+                }
+                out[count] = cpu.apic_id;
+                count += 1;
+            }
+        }
+        count
+    })
+    .unwrap_or(0)
+}
+
+/// Get the PCIe ECAM windows
+pub fn mcfg() -> Mcfg {
+    with_info(|info| info.mcfg).unwrap_or_default()
+}
+
+/// Get the NUMA affinity table
+pub fn srat() -> Option<Srat> {
+    with_info(|info| info.srat.clone()).flatten()
+}
+
+/// Get the VT-d remapping table
+pub fn dmar() -> Option<Dmar> {
+    with_info(|info| info.dmar).flatten()
+}
+
+/// Get the I/O remapping table
+pub fn iort() -> Option<Iort> {
+    with_info(|info| info.iort).flatten()
+}
+
+/// Get the generic timer table
+pub fn gtdt() -> Option<Gtdt> {
+    with_info(|info| info.gtdt).flatten()
+}
+
+/// Get the TPM 2.0 description
+pub fn tpm2() -> Option<Tpm2> {
+    with_info(|info| info.tpm2).flatten()
+}
+
+/// Get the FADT
+pub fn fadt() -> Option<Fadt> {
+    with_info(|info| info.fadt).flatten()
+}
+
+/// Get the HPET description
+pub fn hpet() -> Option<Hpet> {
+    with_info(|info| info.hpet).flatten()
+}
+
+/// Get the FADT reset register and the value to write to it
+///
+/// Safe to call from the panic path: returns None rather than spinning if
+/// the ACPI state is locked.
+pub fn reset_register() -> Option<(GenericAddress, u8)> {
+    if !is_available() {
+        return None;
+    }
+    let info = ACPI.try_lock()?;
+    let fadt = info.fadt.as_ref()?;
+    Some((fadt.reset_reg?, fadt.reset_value))
+}
+
+/// Get the FADT and the S5 sleep type, to power off
+///
+/// Like [`reset_register`], returns None rather than spinning if the ACPI
+/// state is locked.
+pub fn soft_off() -> Option<(Fadt, SleepType)> {
+    if !is_available() {
+        return None;
+    }
+    let info = ACPI.try_lock()?;
+    Some((info.fadt?, info.s5?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_consume_records_dsdt() {
+        let mut buf = [0u8; 244];
+        let len = buf.len();
+        write_header(&mut buf, fadt::FADT_SIGNATURE, len);
+        buf[40..44].copy_from_slice(&0x1234_0000u32.to_le_bytes());
+        fix_checksum(&mut buf, 9);
+
+        let mut info = AcpiInfo::new();
+        info.consume(&Table::parse(&buf).unwrap());
+
+        assert!(info.fadt.is_some());
+        assert_eq!(info.tables().len(), 1);
+        assert_eq!(&info.tables()[0].signature, b"DSDT");
+        assert_eq!(info.tables()[0].paddr, 0x1234_0000);
+    }
+
+    #[test]
+    fn test_queries_before_init() {
+        let mut ids = [0u32; 4];
+        if !is_available() {
+            assert_eq!(cpu_apic_ids(&mut ids), 0);
+            assert!(find_table(b"APIC").is_none());
+            assert!(mcfg().entries().is_empty());
+        }
+    }
+}
+
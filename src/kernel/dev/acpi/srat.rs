@@ -0,0 +1,223 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! SRAT (System Resource Affinity Table)
+//!
+//! Assigns processors (by APIC / x2APIC ID) and physical memory ranges to
+//! proximity domains, which the kernel turns into NUMA nodes. The table
+//! signature is "SRAT".
+
+use super::tables::{read_u32, read_u64, read_u8, Table, SDT_HEADER_LEN};
+use crate::kernel::percpu::SMP_MAX_CPUS;
+
+/// SRAT signature
+pub const SRAT_SIGNATURE: &[u8; 4] = b"SRAT";
+
+/// Maximum memory ranges recorded
+pub const MAX_SRAT_MEMORY: usize = 32;
+
+/// Entry types
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_MEMORY: u8 = 1;
+const ENTRY_LOCAL_X2APIC: u8 = 2;
+
+/// Affinity flags, the same bit in every entry type
+const AFFINITY_ENABLED: u32 = 1 << 0;
+
+/// Memory affinity flags
+const MEMORY_HOT_PLUGGABLE: u32 = 1 << 1;
+
+/// A processor's proximity domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuAffinity {
+    /// APIC ID (x2APIC ID for x2APIC entries)
+    pub apic_id: u32,
+
+    /// Proximity domain
+    pub domain: u32,
+}
+
+impl CpuAffinity {
+    const EMPTY: Self = Self { apic_id: 0, domain: 0 };
+}
+
+/// A physical memory range's proximity domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAffinity {
+    /// Physical base address
+    pub base: u64,
+
+    /// Length in bytes
+    pub length: u64,
+
+    /// Proximity domain
+    pub domain: u32,
+
+    /// Range may be hot-added or removed
+    pub hot_pluggable: bool,
+}
+
+impl MemoryAffinity {
+    const EMPTY: Self = Self { base: 0, length: 0, domain: 0, hot_pluggable: false };
+}
+
+/// Parsed SRAT
+#[derive(Debug, Clone)]
+pub struct Srat {
+    cpus: [CpuAffinity; SMP_MAX_CPUS],
+    cpu_count: usize,
+
+    memory: [MemoryAffinity; MAX_SRAT_MEMORY],
+    memory_count: usize,
+}
+
+impl Srat {
+    /// Empty SRAT
+    pub const fn new() -> Self {
+        Self {
+            cpus: [CpuAffinity::EMPTY; SMP_MAX_CPUS],
+            cpu_count: 0,
+            memory: [MemoryAffinity::EMPTY; MAX_SRAT_MEMORY],
+            memory_count: 0,
+        }
+    }
+
+    /// Parse a validated SRAT
+    ///
+    /// Disabled entries are skipped.
+    pub fn parse(table: &Table) -> Self {
+        let bytes = table.bytes();
+        let mut srat = Self::new();
+
+        // A 4-byte revision and 8 reserved bytes follow the header
+        let mut off = SDT_HEADER_LEN + 12;
+        while off + 2 <= bytes.len() {
+            let entry_type = read_u8(bytes, off);
+            let len = read_u8(bytes, off + 1) as usize;
+            if len < 2 || off + len > bytes.len() {
+                break;
+            }
+            let entry = &bytes[off..off + len];
+
+            match entry_type {
+                ENTRY_LOCAL_APIC if len >= 16 => {
+                    if read_u32(entry, 4) & AFFINITY_ENABLED != 0 {
+                        // Bits 8-31 of the domain are split off at byte 9
+                        let domain = read_u8(entry, 2) as u32
+                            | (read_u8(entry, 9) as u32) << 8
+                            | (read_u8(entry, 10) as u32) << 16
+                            | (read_u8(entry, 11) as u32) << 24;
+                        srat.push_cpu(CpuAffinity { apic_id: read_u8(entry, 3) as u32, domain });
+                    }
+                }
+                ENTRY_LOCAL_X2APIC if len >= 24 => {
+                    if read_u32(entry, 12) & AFFINITY_ENABLED != 0 {
+                        srat.push_cpu(CpuAffinity {
+                            apic_id: read_u32(entry, 8),
+                            domain: read_u32(entry, 4),
+                        });
+                    }
+                }
+                ENTRY_MEMORY if len >= 40 => {
+                    let flags = read_u32(entry, 28);
+                    let length = read_u64(entry, 16);
+                    if flags & AFFINITY_ENABLED != 0 && length != 0 && srat.memory_count < MAX_SRAT_MEMORY {
+                        srat.memory[srat.memory_count] = MemoryAffinity {
+                            base: read_u64(entry, 8),
+                            length,
+                            domain: read_u32(entry, 2),
+                            hot_pluggable: flags & MEMORY_HOT_PLUGGABLE != 0,
+                        };
+                        srat.memory_count += 1;
+                    }
+                }
+                _ => {}
+            }
+
+            off += len;
+        }
+
+        srat
+    }
+
+    fn push_cpu(&mut self, cpu: CpuAffinity) {
+        if self.cpu_count < SMP_MAX_CPUS {
+            self.cpus[self.cpu_count] = cpu;
+            self.cpu_count += 1;
+        }
+    }
+
+    /// Enabled processors
+    pub fn cpus(&self) -> &[CpuAffinity] {
+        &self.cpus[..self.cpu_count]
+    }
+
+    /// Enabled memory ranges
+    pub fn memory(&self) -> &[MemoryAffinity] {
+        &self.memory[..self.memory_count]
+    }
+}
+
+impl Default for Srat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_srat_parse() {
+        let mut buf = [0u8; 256];
+        let mut off = SDT_HEADER_LEN + 12;
+
+        // APIC 0 in domain 0, APIC 1 in domain 0x0100_0001, APIC 2 disabled
+        for (apic_id, domain_lo, domain_hi, flags) in [(0u8, 0u8, 0u8, 1u32), (1, 1, 1, 1), (2, 1, 0, 0)] {
+            buf[off] = ENTRY_LOCAL_APIC;
+            buf[off + 1] = 16;
+            buf[off + 2] = domain_lo;
+            buf[off + 3] = apic_id;
+            buf[off + 4..off + 8].copy_from_slice(&flags.to_le_bytes());
+            buf[off + 11] = domain_hi;
+            off += 16;
+        }
+
+        buf[off] = ENTRY_LOCAL_X2APIC;
+        buf[off + 1] = 24;
+        buf[off + 4..off + 8].copy_from_slice(&3u32.to_le_bytes());
+        buf[off + 8..off + 12].copy_from_slice(&0x100u32.to_le_bytes());
+        buf[off + 12..off + 16].copy_from_slice(&1u32.to_le_bytes());
+        off += 24;
+
+        buf[off] = ENTRY_MEMORY;
+        buf[off + 1] = 40;
+        buf[off + 2..off + 6].copy_from_slice(&1u32.to_le_bytes());
+        buf[off + 8..off + 16].copy_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        buf[off + 16..off + 24].copy_from_slice(&0x4000_0000u64.to_le_bytes());
+        buf[off + 28..off + 32].copy_from_slice(&(AFFINITY_ENABLED | MEMORY_HOT_PLUGGABLE).to_le_bytes());
+        off += 40;
+
+        write_header(&mut buf, SRAT_SIGNATURE, off);
+        fix_checksum(&mut buf[..off], 9);
+
+        let table = Table::parse(&buf[..off]).unwrap();
+        let srat = Srat::parse(&table);
+
+        assert_eq!(srat.cpus().len(), 3);
+        assert_eq!(srat.cpus()[0], CpuAffinity { apic_id: 0, domain: 0 });
+        assert_eq!(srat.cpus()[1], CpuAffinity { apic_id: 1, domain: 0x0100_0001 });
+        assert_eq!(srat.cpus()[2], CpuAffinity { apic_id: 0x100, domain: 3 });
+
+        assert_eq!(srat.memory().len(), 1);
+        assert_eq!(srat.memory()[0].base, 0x1_0000_0000);
+        assert_eq!(srat.memory()[0].length, 0x4000_0000);
+        assert_eq!(srat.memory()[0].domain, 1);
+        assert!(srat.memory()[0].hot_pluggable);
+    }
+}
@@ -0,0 +1,380 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ACPI Table Access
+//!
+//! RSDP and System Description Table (SDT) header parsing, checksum
+//! validation and bounded mapping of tables from physical memory.
+//!
+//! All parsing operates on byte slices whose length has been validated
+//! against the table header, so a corrupt table can at worst produce
+//! wrong values, never out-of-bounds reads.
+
+use crate::kernel::mmu::phys_to_virt;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+/// RSDP signature
+pub const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// ACPI 1.0 RSDP length
+pub const RSDP_V1_LEN: usize = 20;
+
+/// ACPI 2.0+ RSDP length
+pub const RSDP_V2_LEN: usize = 36;
+
+/// Length of the common SDT header
+pub const SDT_HEADER_LEN: usize = 36;
+
+/// Largest table we are willing to map
+pub const MAX_TABLE_LEN: usize = 1 << 20;
+
+/// ============================================================================
+/// Byte Helpers
+/// ============================================================================
+
+pub(crate) fn read_u8(buf: &[u8], off: usize) -> u8 {
+    buf.get(off).copied().unwrap_or(0)
+}
+
+pub(crate) fn read_u16(buf: &[u8], off: usize) -> u16 {
+    match buf.get(off..off + 2) {
+        Some(b) => u16::from_le_bytes([b[0], b[1]]),
+        None => 0,
+    }
+}
+
+pub(crate) fn read_u32(buf: &[u8], off: usize) -> u32 {
+    match buf.get(off..off + 4) {
+        Some(b) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        None => 0,
+    }
+}
+
+pub(crate) fn read_u64(buf: &[u8], off: usize) -> u64 {
+    read_u32(buf, off) as u64 | (read_u32(buf, off + 4) as u64) << 32
+}
+
+/// Check that all bytes sum to zero (mod 256)
+pub fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+/// ============================================================================
+/// Generic Address Structure
+/// ============================================================================
+
+/// Generic Address Structure address space IDs
+pub const GAS_SYSTEM_MEMORY: u8 = 0;
+pub const GAS_SYSTEM_IO: u8 = 1;
+
+/// Generic Address Structure (GAS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericAddress {
+    /// Address space (memory, I/O, PCI config, ...)
+    pub space_id: u8,
+
+    /// Register width in bits
+    pub bit_width: u8,
+
+    /// Register offset in bits
+    pub bit_offset: u8,
+
+    /// Access size (1 = byte ... 4 = qword)
+    pub access_size: u8,
+
+    /// Address within the space
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// Parse a 12-byte GAS at `off`
+    pub fn parse(buf: &[u8], off: usize) -> Self {
+        Self {
+            space_id: read_u8(buf, off),
+            bit_width: read_u8(buf, off + 1),
+            bit_offset: read_u8(buf, off + 2),
+            access_size: read_u8(buf, off + 3),
+            address: read_u64(buf, off + 4),
+        }
+    }
+}
+
+/// ============================================================================
+/// RSDP
+/// ============================================================================
+
+/// Root System Description Pointer
+#[derive(Debug, Clone, Copy)]
+pub struct Rsdp {
+    /// ACPI revision (0 = 1.0, 2+ = 2.0 and later)
+    pub revision: u8,
+
+    /// OEM identifier
+    pub oem_id: [u8; 6],
+
+    /// Physical address of the RSDT
+    pub rsdt_address: u32,
+
+    /// Physical address of the XSDT (0 for ACPI 1.0)
+    pub xsdt_address: u64,
+}
+
+impl Rsdp {
+    /// Parse and validate an RSDP
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < RSDP_V1_LEN || &bytes[..8] != RSDP_SIGNATURE {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+        if !checksum_ok(&bytes[..RSDP_V1_LEN]) {
+            return Err(RX_ERR_IO);
+        }
+
+        let mut oem_id = [0u8; 6];
+        oem_id.copy_from_slice(&bytes[9..15]);
+
+        let revision = bytes[15];
+        let mut rsdp = Self {
+            revision,
+            oem_id,
+            rsdt_address: read_u32(bytes, 16),
+            xsdt_address: 0,
+        };
+
+        if revision >= 2 {
+            let length = read_u32(bytes, 20) as usize;
+            if length < RSDP_V2_LEN || length > bytes.len() || !checksum_ok(&bytes[..length]) {
+                return Err(RX_ERR_IO);
+            }
+            rsdp.xsdt_address = read_u64(bytes, 24);
+        }
+
+        Ok(rsdp)
+    }
+}
+
+/// ============================================================================
+/// System Description Tables
+/// ============================================================================
+
+/// Common SDT header
+#[derive(Debug, Clone, Copy)]
+pub struct SdtHeader {
+    /// Table signature (e.g. "APIC")
+    pub signature: [u8; 4],
+
+    /// Total table length including the header
+    pub length: u32,
+
+    /// Table revision
+    pub revision: u8,
+
+    /// OEM identifier
+    pub oem_id: [u8; 6],
+
+    /// OEM table identifier
+    pub oem_table_id: [u8; 8],
+}
+
+/// A validated ACPI table
+#[derive(Debug, Clone, Copy)]
+pub struct Table<'a> {
+    /// Parsed header
+    pub header: SdtHeader,
+
+    /// Whole table, exactly `header.length` bytes
+    bytes: &'a [u8],
+}
+
+impl<'a> Table<'a> {
+    /// Parse and validate a table (length and checksum)
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() < SDT_HEADER_LEN {
+            return Err(RX_ERR_BUFFER_TOO_SMALL);
+        }
+
+        let length = read_u32(bytes, 4) as usize;
+        if length < SDT_HEADER_LEN || length > bytes.len() {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        let bytes = &bytes[..length];
+        if !checksum_ok(bytes) {
+            return Err(RX_ERR_IO);
+        }
+
+        let mut signature = [0u8; 4];
+        signature.copy_from_slice(&bytes[0..4]);
+        let mut oem_id = [0u8; 6];
+        oem_id.copy_from_slice(&bytes[10..16]);
+        let mut oem_table_id = [0u8; 8];
+        oem_table_id.copy_from_slice(&bytes[16..24]);
+
+        Ok(Self {
+            header: SdtHeader {
+                signature,
+                length: length as u32,
+                revision: bytes[8],
+                oem_id,
+                oem_table_id,
+            },
+            bytes,
+        })
+    }
+
+    /// Table signature
+    pub fn signature(&self) -> &[u8; 4] {
+        &self.header.signature
+    }
+
+    /// Whole table, including the header
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Iterate over the entries of an RSDT (4-byte) or XSDT (8-byte)
+    pub fn root_entries(&self, entry_size: usize) -> impl Iterator<Item = u64> + 'a {
+        let bytes = self.bytes;
+        (SDT_HEADER_LEN..bytes.len())
+            .step_by(entry_size)
+            .filter(move |off| off + entry_size <= bytes.len())
+            .map(move |off| {
+                if entry_size == 8 {
+                    read_u64(bytes, off)
+                } else {
+                    read_u32(bytes, off) as u64
+                }
+            })
+    }
+}
+
+/// ============================================================================
+/// Physical Mapping
+/// ============================================================================
+
+/// Map `len` bytes of physical memory as a byte slice
+///
+/// ACPI tables live below 4 GiB in memory that every boot path leaves
+/// mapped, so the kernel physmap translation is sufficient.
+///
+/// # Safety
+///
+/// `paddr..paddr + len` must be readable physical memory.
+pub unsafe fn map_phys(paddr: u64, len: usize) -> Result<&'static [u8]> {
+    if paddr == 0 || len == 0 || len > MAX_TABLE_LEN {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    if paddr.checked_add(len as u64).is_none() {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+
+    let vaddr = phys_to_virt(paddr);
+    Ok(core::slice::from_raw_parts(vaddr as *const u8, len))
+}
+
+/// Map and validate the RSDP at `paddr`
+///
+/// # Safety
+///
+/// `paddr` must point at readable physical memory.
+pub unsafe fn map_rsdp(paddr: u64) -> Result<Rsdp> {
+    let v1 = map_phys(paddr, RSDP_V1_LEN)?;
+    if v1[15] >= 2 {
+        Rsdp::parse(map_phys(paddr, RSDP_V2_LEN)?)
+    } else {
+        Rsdp::parse(v1)
+    }
+}
+
+/// Map and validate the table at `paddr`
+///
+/// The header is mapped first to learn the table length, then the whole
+/// table is mapped and checksummed.
+///
+/// # Safety
+///
+/// `paddr` must point at readable physical memory.
+pub unsafe fn map_table(paddr: u64) -> Result<Table<'static>> {
+    let header = map_phys(paddr, SDT_HEADER_LEN)?;
+    let length = read_u32(header, 4) as usize;
+    if length < SDT_HEADER_LEN {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+
+    Table::parse(map_phys(paddr, length)?)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Fix up the checksum byte of a table built in a test buffer
+    pub(crate) fn fix_checksum(bytes: &mut [u8], checksum_offset: usize) {
+        bytes[checksum_offset] = 0;
+        let sum = bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+        bytes[checksum_offset] = 0u8.wrapping_sub(sum);
+    }
+
+    /// Build an SDT header into `buf`
+    pub(crate) fn write_header(buf: &mut [u8], signature: &[u8; 4], length: usize) {
+        buf[0..4].copy_from_slice(signature);
+        buf[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+        buf[8] = 1;
+        buf[10..16].copy_from_slice(b"RUSTUX");
+        buf[16..24].copy_from_slice(b"TESTTABL");
+    }
+
+    #[test]
+    fn test_rsdp_v2() {
+        let mut rsdp = [0u8; RSDP_V2_LEN];
+        rsdp[..8].copy_from_slice(RSDP_SIGNATURE);
+        rsdp[9..15].copy_from_slice(b"RUSTUX");
+        rsdp[15] = 2;
+        rsdp[16..20].copy_from_slice(&0x7fe0_0000u32.to_le_bytes());
+        rsdp[20..24].copy_from_slice(&(RSDP_V2_LEN as u32).to_le_bytes());
+        rsdp[24..32].copy_from_slice(&0x7fe1_0000u64.to_le_bytes());
+        fix_checksum(&mut rsdp[..RSDP_V1_LEN], 8);
+        fix_checksum(&mut rsdp, 32);
+
+        let parsed = Rsdp::parse(&rsdp).unwrap();
+        assert_eq!(parsed.revision, 2);
+        assert_eq!(parsed.rsdt_address, 0x7fe0_0000);
+        assert_eq!(parsed.xsdt_address, 0x7fe1_0000);
+    }
+
+    #[test]
+    fn test_rsdp_bad_checksum() {
+        let mut rsdp = [0u8; RSDP_V1_LEN];
+        rsdp[..8].copy_from_slice(RSDP_SIGNATURE);
+        rsdp[8] = 1;
+        assert_eq!(Rsdp::parse(&rsdp).unwrap_err(), RX_ERR_IO);
+    }
+
+    #[test]
+    fn test_table_parse_and_root_entries() {
+        let mut xsdt = [0u8; SDT_HEADER_LEN + 16];
+        let len = xsdt.len();
+        write_header(&mut xsdt, b"XSDT", len);
+        xsdt[36..44].copy_from_slice(&0x1000u64.to_le_bytes());
+        xsdt[44..52].copy_from_slice(&0x2000u64.to_le_bytes());
+        fix_checksum(&mut xsdt, 9);
+
+        let table = Table::parse(&xsdt).unwrap();
+        assert_eq!(table.signature(), b"XSDT");
+
+        let mut entries = table.root_entries(8);
+        assert_eq!(entries.next(), Some(0x1000));
+        assert_eq!(entries.next(), Some(0x2000));
+        assert_eq!(entries.next(), None);
+    }
+
+    #[test]
+    fn test_table_rejects_bad_length() {
+        let mut buf = [0u8; SDT_HEADER_LEN];
+        write_header(&mut buf, b"APIC", 4096);
+        assert_eq!(Table::parse(&buf).unwrap_err(), RX_ERR_OUT_OF_RANGE);
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TPM2 (Trusted Platform Module 2.0) Table
+//!
+//! Says how to reach the TPM: the start method selects the interface and,
+//! for CRB, the table gives the address of the control area. The table
+//! signature is "TPM2".
+
+use super::tables::{read_u32, read_u64, Table};
+
+/// TPM2 signature
+pub const TPM2_SIGNATURE: &[u8; 4] = b"TPM2";
+
+/// Start methods
+pub const START_METHOD_ACPI: u32 = 2;
+pub const START_METHOD_FIFO: u32 = 6;
+pub const START_METHOD_CRB: u32 = 7;
+pub const START_METHOD_CRB_ACPI: u32 = 8;
+
+/// Shortest valid table: header, platform class, control area, start method
+const TPM2_MIN_LEN: usize = 52;
+
+/// Parsed TPM2 table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tpm2 {
+    /// Physical address of the CRB control area (0 for FIFO)
+    pub control_area: u64,
+
+    /// How commands are started (`START_METHOD_*`)
+    pub start_method: u32,
+}
+
+impl Tpm2 {
+    /// Parse a validated TPM2 table
+    pub fn parse(table: &Table) -> Option<Self> {
+        let b = table.bytes();
+        if b.len() < TPM2_MIN_LEN {
+            return None;
+        }
+
+        Some(Self {
+            control_area: read_u64(b, 40),
+            start_method: read_u32(b, 48),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::acpi::tables::tests::{fix_checksum, write_header};
+
+    #[test]
+    fn test_tpm2_parse() {
+        let mut buf = [0u8; 76];
+        let len = buf.len();
+        write_header(&mut buf, TPM2_SIGNATURE, len);
+        buf[40..48].copy_from_slice(&0xfed4_0040u64.to_le_bytes());
+        buf[48..52].copy_from_slice(&START_METHOD_CRB.to_le_bytes());
+        fix_checksum(&mut buf, 9);
+
+        let tpm2 = Tpm2::parse(&Table::parse(&buf).unwrap()).unwrap();
+        assert_eq!(tpm2.control_area, 0xfed4_0040);
+        assert_eq!(tpm2.start_method, START_METHOD_CRB);
+
+        write_header(&mut buf, TPM2_SIGNATURE, 48);
+        fix_checksum(&mut buf[..48], 9);
+        assert!(Tpm2::parse(&Table::parse(&buf[..48]).unwrap()).is_none());
+    }
+}
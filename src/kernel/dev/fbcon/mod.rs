@@ -0,0 +1,450 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Early Framebuffer Console
+//!
+//! This module renders kernel log output onto the linear framebuffer
+//! handed over by the loader (UEFI GOP). It is an alternative to serial
+//! output for machines without a UART and works before any display
+//! driver or heap is available.
+//!
+//! # Design
+//!
+//! - Built-in 8x8 bitmap font, optionally scaled 2x on large displays
+//! - 32bpp RGB/BGR linear framebuffers only
+//! - Scrolls by moving framebuffer memory; no back buffer
+//! - ANSI SGR color sequences from the logger map to the 8 basic colors,
+//!   other escape sequences are swallowed
+//!
+//! # Command Line
+//!
+//! - `kernel.fbcon=false` disables the console even if a framebuffer exists
+
+
+pub mod font;
+
+use crate::kernel::handoff::{FramebufferFormat, FramebufferInfo};
+use crate::kernel::sync::spin::SpinMutex;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Import logging macros
+use crate::log_info;
+
+/// ============================================================================
+/// Colors
+/// ============================================================================
+
+/// Default foreground (light gray), as 0xRRGGBB
+pub const DEFAULT_FG: u32 = 0xC0C0C0;
+
+/// Default background (black)
+pub const DEFAULT_BG: u32 = 0x000000;
+
+/// ANSI colors 30-37, as 0xRRGGBB
+const ANSI_COLORS: [u32; 8] = [
+    0x000000, // black
+    0xE04040, // red
+    0x40C040, // green
+    0xE0C040, // yellow
+    0x4080E0, // blue
+    0xC040C0, // magenta
+    0x40C0C0, // cyan
+    0xC0C0C0, // white
+];
+
+/// ============================================================================
+/// Console State
+/// ============================================================================
+
+/// Escape-sequence parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscState {
+    /// Plain text
+    Normal,
+
+    /// Saw ESC
+    Escape,
+
+    /// Inside CSI (`ESC [`), accumulating a parameter
+    Csi,
+}
+
+/// Framebuffer console
+pub struct FbCon {
+    /// Virtual address of the framebuffer
+    base: *mut u32,
+
+    /// Width in pixels
+    width: u32,
+
+    /// Height in pixels
+    height: u32,
+
+    /// Pixels per scan line
+    stride: u32,
+
+    /// Pixel format
+    format: FramebufferFormat,
+
+    /// Glyph scale factor
+    scale: u32,
+
+    /// Text columns
+    cols: u32,
+
+    /// Text rows
+    rows: u32,
+
+    /// Cursor column
+    x: u32,
+
+    /// Cursor row
+    y: u32,
+
+    /// Foreground color (0xRRGGBB)
+    fg: u32,
+
+    /// Background color (0xRRGGBB)
+    bg: u32,
+
+    /// Escape parser state
+    esc: EscState,
+
+    /// Current CSI parameter
+    esc_param: u32,
+}
+
+unsafe impl Send for FbCon {}
+
+impl FbCon {
+    /// Create a console over a framebuffer
+    ///
+    /// # Safety
+    ///
+    /// `base` must map at least `stride * height` 32-bit pixels.
+    pub unsafe fn new(base: *mut u32, width: u32, height: u32, stride: u32, format: FramebufferFormat) -> Self {
+        // 8x8 glyphs are unreadable on high resolution panels
+        let scale = if width >= 1600 { 2 } else { 1 };
+        let cell_w = font::FONT_WIDTH * scale;
+        let cell_h = font::FONT_HEIGHT * scale;
+
+        Self {
+            base,
+            width,
+            height,
+            stride,
+            format,
+            scale,
+            cols: width / cell_w,
+            rows: height / cell_h,
+            x: 0,
+            y: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            esc: EscState::Normal,
+            esc_param: 0,
+        }
+    }
+
+    /// Text dimensions (columns, rows)
+    pub fn size(&self) -> (u32, u32) {
+        (self.cols, self.rows)
+    }
+
+    /// Cursor position (column, row)
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+
+    /// Convert 0xRRGGBB to the framebuffer's pixel format
+    fn pixel(&self, rgb: u32) -> u32 {
+        match self.format {
+            // Memory order B,G,R,X == little-endian 0x00RRGGBB
+            FramebufferFormat::BGR => rgb,
+            // Memory order R,G,B,X == little-endian 0x00BBGGRR
+            FramebufferFormat::RGB => {
+                ((rgb & 0xFF) << 16) | (rgb & 0xFF00) | ((rgb >> 16) & 0xFF)
+            }
+            FramebufferFormat::None => 0,
+        }
+    }
+
+    /// Fill a pixel rectangle
+    fn fill(&mut self, x: u32, y: u32, w: u32, h: u32, rgb: u32) {
+        let color = self.pixel(rgb);
+        for row in y..core::cmp::min(y + h, self.height) {
+            let line = unsafe { self.base.add((row * self.stride) as usize) };
+            for col in x..core::cmp::min(x + w, self.width) {
+                unsafe { line.add(col as usize).write_volatile(color) };
+            }
+        }
+    }
+
+    /// Clear the screen and home the cursor
+    pub fn clear(&mut self) {
+        let (w, h, bg) = (self.width, self.height, self.bg);
+        self.fill(0, 0, w, h, bg);
+        self.x = 0;
+        self.y = 0;
+    }
+
+    /// Draw a glyph at a text cell
+    fn draw_glyph(&mut self, col: u32, row: u32, ch: u8) {
+        let glyph = font::glyph(ch);
+        let fg = self.pixel(self.fg);
+        let bg = self.pixel(self.bg);
+        let scale = self.scale;
+        let px = col * font::FONT_WIDTH * scale;
+        let py = row * font::FONT_HEIGHT * scale;
+
+        for (gy, bits) in glyph.iter().enumerate() {
+            for sy in 0..scale {
+                let y = py + gy as u32 * scale + sy;
+                let line = unsafe { self.base.add((y * self.stride) as usize) };
+                for gx in 0..font::FONT_WIDTH {
+                    let color = if bits & (1 << gx) != 0 { fg } else { bg };
+                    for sx in 0..scale {
+                        let x = px + gx * scale + sx;
+                        unsafe { line.add(x as usize).write_volatile(color) };
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scroll the text area up by one row
+    fn scroll(&mut self) {
+        let cell_h = font::FONT_HEIGHT * self.scale;
+        let text_h = self.rows * cell_h;
+        let row_pixels = (cell_h * self.stride) as usize;
+        let total = (text_h * self.stride) as usize;
+
+        unsafe {
+            core::ptr::copy(self.base.add(row_pixels), self.base, total - row_pixels);
+        }
+
+        let (w, bg) = (self.width, self.bg);
+        self.fill(0, text_h - cell_h, w, cell_h, bg);
+    }
+
+    /// Move to the start of the next line, scrolling if needed
+    fn newline(&mut self) {
+        self.x = 0;
+        if self.y + 1 >= self.rows {
+            self.scroll();
+        } else {
+            self.y += 1;
+        }
+    }
+
+    /// Apply an SGR (`ESC [ n m`) parameter
+    fn apply_sgr(&mut self, param: u32) {
+        match param {
+            0 => {
+                self.fg = DEFAULT_FG;
+                self.bg = DEFAULT_BG;
+            }
+            30..=37 => self.fg = ANSI_COLORS[(param - 30) as usize],
+            39 => self.fg = DEFAULT_FG,
+            40..=47 => self.bg = ANSI_COLORS[(param - 40) as usize],
+            49 => self.bg = DEFAULT_BG,
+            _ => {}
+        }
+    }
+
+    /// Write one byte, interpreting control characters
+    pub fn putc(&mut self, c: u8) {
+        if self.rows == 0 || self.cols == 0 {
+            return;
+        }
+
+        match self.esc {
+            EscState::Escape => {
+                self.esc = if c == b'[' { EscState::Csi } else { EscState::Normal };
+                self.esc_param = 0;
+                return;
+            }
+            EscState::Csi => {
+                match c {
+                    b'0'..=b'9' => {
+                        self.esc_param = self.esc_param.saturating_mul(10) + (c - b'0') as u32;
+                    }
+                    b';' => {
+                        self.apply_sgr(self.esc_param);
+                        self.esc_param = 0;
+                    }
+                    b'm' => {
+                        self.apply_sgr(self.esc_param);
+                        self.esc = EscState::Normal;
+                    }
+                    // Any other final byte ends the sequence unhandled
+                    0x40..=0x7E => self.esc = EscState::Normal,
+                    _ => {}
+                }
+                return;
+            }
+            EscState::Normal => {}
+        }
+
+        match c {
+            0x1B => self.esc = EscState::Escape,
+            b'\n' => self.newline(),
+            b'\r' => self.x = 0,
+            b'\t' => {
+                let next = (self.x + 8) & !7;
+                if next >= self.cols {
+                    self.newline();
+                } else {
+                    self.x = next;
+                }
+            }
+            0x08 => {
+                if self.x > 0 {
+                    self.x -= 1;
+                }
+            }
+            _ => {
+                if self.x >= self.cols {
+                    self.newline();
+                }
+                let (x, y) = (self.x, self.y);
+                self.draw_glyph(x, y, c);
+                self.x += 1;
+            }
+        }
+    }
+
+    /// Write a string
+    pub fn write_str(&mut self, s: &str) {
+        for b in s.bytes() {
+            self.putc(b);
+        }
+    }
+}
+
+/// ============================================================================
+/// Global Console
+/// ============================================================================
+
+/// Global framebuffer console
+static FBCON: SpinMutex<Option<FbCon>> = SpinMutex::new(None);
+
+/// Fast check so the logger does not touch the lock when disabled
+static FBCON_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Initialize the console from a loader framebuffer description
+///
+/// Returns false if the framebuffer format is unsupported.
+pub fn init(info: &FramebufferInfo) -> bool {
+    if info.format == FramebufferFormat::None || info.width == 0 || info.height == 0 {
+        return false;
+    }
+
+    // The loader leaves physical memory identity mapped
+    let base = crate::kernel::mmu::phys_to_virt(info.base) as *mut u32;
+
+    let mut con = unsafe { FbCon::new(base, info.width, info.height, info.stride, info.format) };
+    con.clear();
+    let (cols, rows) = con.size();
+
+    *FBCON.lock() = Some(con);
+    FBCON_ENABLED.store(true, Ordering::Release);
+
+    log_info!("fbcon: {}x{} framebuffer, {}x{} text", info.width, info.height, cols, rows);
+    true
+}
+
+/// Initialize from the boot handoff, honoring `kernel.fbcon`
+pub fn init_from_handoff() {
+    if !crate::kernel::cmdline::cmdline_get_bool("kernel.fbcon", true) {
+        return;
+    }
+
+    if let Some(fb) = crate::kernel::handoff::get().and_then(|h| h.framebuffer()) {
+        init(fb);
+    }
+}
+
+/// Check whether the framebuffer console is active
+#[inline]
+pub fn is_enabled() -> bool {
+    FBCON_ENABLED.load(Ordering::Acquire)
+}
+
+/// Stop rendering to the framebuffer (e.g. when a display driver takes over)
+pub fn disable() {
+    FBCON_ENABLED.store(false, Ordering::Release);
+}
+
+/// Write a string to the framebuffer console
+///
+/// Uses `try_lock` so output from a nested context (interrupt or panic
+/// while printing) is dropped rather than deadlocking.
+pub fn write_str(s: &str) {
+    if !is_enabled() {
+        return;
+    }
+
+    if let Some(mut guard) = FBCON.try_lock() {
+        if let Some(con) = guard.as_mut() {
+            con.write_str(s);
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn make_con(buf: &mut [u32], w: u32, h: u32) -> FbCon {
+        unsafe { FbCon::new(buf.as_mut_ptr(), w, h, w, FramebufferFormat::BGR) }
+    }
+
+    #[test]
+    fn test_fbcon_geometry() {
+        let mut buf = vec![0u32; 64 * 32];
+        let con = make_con(&mut buf, 64, 32);
+        assert_eq!(con.size(), (8, 4));
+    }
+
+    #[test]
+    fn test_fbcon_wrap_and_scroll() {
+        let mut buf = vec![0u32; 16 * 16];
+        let mut con = make_con(&mut buf, 16, 16);
+        con.write_str("abc");
+        assert_eq!(con.cursor(), (1, 1));
+        con.write_str("\n\n");
+        // Two rows only: cursor stays on last row after scrolling
+        assert_eq!(con.cursor(), (0, 1));
+    }
+
+    #[test]
+    fn test_fbcon_escape_sequences_not_drawn() {
+        let mut buf = vec![0u32; 64 * 8];
+        let mut con = make_con(&mut buf, 64, 8);
+        con.write_str("\x1b[31mX\x1b[0m");
+        assert_eq!(con.cursor(), (1, 0));
+        assert_eq!(con.fg, DEFAULT_FG);
+    }
+
+    #[test]
+    fn test_fbcon_rgb_swizzle() {
+        let mut buf = vec![0u32; 8 * 8];
+        let con = unsafe { FbCon::new(buf.as_mut_ptr(), 8, 8, 8, FramebufferFormat::RGB) };
+        assert_eq!(con.pixel(0x112233), 0x332211);
+    }
+
+    #[test]
+    fn test_font_glyph_lookup() {
+        assert_eq!(font::glyph(b' '), &[0u8; 8]);
+        assert_ne!(font::glyph(b'A'), font::glyph(0x01));
+    }
+}
@@ -0,0 +1,595 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Flattened Device Tree (FDT) Parser
+//!
+//! ARM and RISC-V platforms describe their hardware with a devicetree
+//! blob (DTB) passed by the bootloader or firmware. This module parses
+//! the blob in place without allocating.
+//!
+//! # Format
+//!
+//! A DTB is a big-endian header followed by a memory reservation block,
+//! a structure block of tokens (`BEGIN_NODE`, `PROP`, `END_NODE`, ...)
+//! and a strings block holding property names.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let fdt = unsafe { Fdt::from_phys(dtb_paddr)? };
+//! if let Some(chosen) = fdt.find_node("/chosen") {
+//!     let bootargs = chosen.property("bootargs");
+//! }
+//! ```
+
+pub mod platform;
+
+pub use platform::{init, platform_info, PlatformInfo, PsciMethod, UartInfo, UartKind};
+#[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+pub use platform::platform_init;
+
+use crate::kernel::mmu::phys_to_virt;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+/// FDT header magic
+pub const FDT_MAGIC: u32 = 0xd00d_feed;
+
+/// Oldest structure version we understand
+const FDT_MIN_COMPAT_VERSION: u32 = 16;
+
+/// Size of the FDT header
+const FDT_HEADER_LEN: usize = 40;
+
+/// Largest blob we are willing to map
+pub const FDT_MAX_SIZE: usize = 2 * 1024 * 1024;
+
+/// Structure block tokens
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+fn be32(buf: &[u8], off: usize) -> Option<u32> {
+    buf.get(off..off + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn be64(buf: &[u8], off: usize) -> Option<u64> {
+    Some((be32(buf, off)? as u64) << 32 | be32(buf, off + 4)? as u64)
+}
+
+fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// NUL-terminated string at `off`
+fn c_str(buf: &[u8], off: usize) -> Option<&[u8]> {
+    let rest = buf.get(off..)?;
+    let len = rest.iter().position(|&c| c == 0)?;
+    Some(&rest[..len])
+}
+
+/// ============================================================================
+/// Blob
+/// ============================================================================
+
+/// A validated device tree blob
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    structs: &'a [u8],
+    strings: &'a [u8],
+    rsvmap_off: usize,
+
+    /// Physical ID of the boot CPU
+    pub boot_cpuid: u32,
+}
+
+/// A structure block token
+enum Token<'a> {
+    BeginNode(&'a [u8]),
+    EndNode,
+    Prop(Property<'a>),
+    Nop,
+    End,
+}
+
+/// A property
+#[derive(Clone, Copy)]
+pub struct Property<'a> {
+    /// Property name
+    pub name: &'a [u8],
+
+    /// Raw big-endian value
+    pub value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    /// Value as a NUL-terminated string
+    pub fn as_str(&self) -> Option<&'a str> {
+        let value = self.value;
+        let len = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+        core::str::from_utf8(&value[..len]).ok()
+    }
+
+    /// `index`-th 32-bit cell
+    pub fn cell(&self, index: usize) -> Option<u32> {
+        be32(self.value, index * 4)
+    }
+
+    /// Value as a single u32 or u64 cell group
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => be32(self.value, 0).map(|v| v as u64),
+            8 => be64(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the strings of a string-list property
+    pub fn strings(&self) -> impl Iterator<Item = &'a [u8]> {
+        let value = self.value;
+        value.split(|&c| c == 0).filter(|s| !s.is_empty())
+    }
+}
+
+impl<'a> Fdt<'a> {
+    /// Parse and validate a blob
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if be32(data, 0) != Some(FDT_MAGIC) {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+
+        let header = |i: usize| be32(data, i * 4).unwrap_or(0) as usize;
+        let total = header(1);
+        let off_struct = header(2);
+        let off_strings = header(3);
+        let off_rsvmap = header(4);
+        let last_comp_version = header(6) as u32;
+        let size_strings = header(8);
+        let size_struct = header(9);
+
+        if total < FDT_HEADER_LEN || total > data.len() {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        if last_comp_version > 17 || header(5) < FDT_MIN_COMPAT_VERSION as usize {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        let data = &data[..total];
+        let structs = data
+            .get(off_struct..off_struct.saturating_add(size_struct))
+            .ok_or(RX_ERR_OUT_OF_RANGE)?;
+        let strings = data
+            .get(off_strings..off_strings.saturating_add(size_strings))
+            .ok_or(RX_ERR_OUT_OF_RANGE)?;
+        if off_rsvmap >= total {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        Ok(Self {
+            data,
+            structs,
+            strings,
+            rsvmap_off: off_rsvmap,
+            boot_cpuid: header(7) as u32,
+        })
+    }
+
+    /// Map and parse the blob at physical address `paddr`
+    ///
+    /// # Safety
+    ///
+    /// `paddr` must point at readable memory holding a device tree.
+    pub unsafe fn from_phys(paddr: u64) -> Result<Fdt<'static>> {
+        if paddr == 0 || paddr & 7 != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        let vaddr = phys_to_virt(paddr) as *const u8;
+        let header = core::slice::from_raw_parts(vaddr, FDT_HEADER_LEN);
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+
+        let total = be32(header, 4).unwrap_or(0) as usize;
+        if total < FDT_HEADER_LEN || total > FDT_MAX_SIZE {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        Fdt::parse(core::slice::from_raw_parts(vaddr, total))
+    }
+
+    /// Total size of the blob in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Iterate over the memory reservation block as (base, size)
+    pub fn reservations(&self) -> impl Iterator<Item = (u64, u64)> + 'a {
+        let data = self.data;
+        let mut off = self.rsvmap_off;
+        core::iter::from_fn(move || {
+            let base = be64(data, off)?;
+            let size = be64(data, off + 8)?;
+            off += 16;
+            if base == 0 && size == 0 {
+                None
+            } else {
+                Some((base, size))
+            }
+        })
+    }
+
+    fn token(&self, off: usize) -> Option<(Token<'a>, usize)> {
+        let structs = self.structs;
+        let tok = be32(structs, off)?;
+        let off = off + 4;
+        match tok {
+            FDT_BEGIN_NODE => {
+                let name = c_str(structs, off)?;
+                Some((Token::BeginNode(name), align4(off + name.len() + 1)))
+            }
+            FDT_END_NODE => Some((Token::EndNode, off)),
+            FDT_PROP => {
+                let len = be32(structs, off)? as usize;
+                let nameoff = be32(structs, off + 4)? as usize;
+                let value = structs.get(off + 8..off + 8 + len)?;
+                let name = c_str(self.strings, nameoff)?;
+                Some((Token::Prop(Property { name, value }), align4(off + 8 + len)))
+            }
+            FDT_NOP => Some((Token::Nop, off)),
+            FDT_END => Some((Token::End, off)),
+            _ => None,
+        }
+    }
+
+    /// Iterate over all nodes in depth-first order
+    pub fn nodes(&self) -> NodeIter<'a> {
+        NodeIter { fdt: *self, off: 0, depth: 0 }
+    }
+
+    /// Root node
+    pub fn root(&self) -> Option<Node<'a>> {
+        self.nodes().next()
+    }
+
+    /// Find a node by absolute path (e.g. "/cpus/cpu@0")
+    ///
+    /// A path component without a unit address matches any unit address.
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        let mut node = self.root()?;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            node = node.children().find(|child| child.name_matches(component))?;
+        }
+        Some(node)
+    }
+
+    /// Find the first node whose compatible list contains `compatible`
+    pub fn find_compatible(&self, compatible: &str) -> Option<Node<'a>> {
+        self.nodes().find(|n| n.is_compatible(compatible))
+    }
+
+    /// Find a node by phandle
+    pub fn find_phandle(&self, phandle: u32) -> Option<Node<'a>> {
+        self.nodes().find(|n| {
+            n.property("phandle")
+                .or_else(|| n.property("linux,phandle"))
+                .and_then(|p| p.cell(0))
+                == Some(phandle)
+        })
+    }
+}
+
+/// ============================================================================
+/// Nodes
+/// ============================================================================
+
+/// A node in the structure block
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+
+    /// Node name including unit address (root is "")
+    pub name: &'a [u8],
+
+    /// Depth (root is 0)
+    pub depth: usize,
+
+    /// Offset of the first token after the node name
+    props_off: usize,
+}
+
+impl<'a> Node<'a> {
+    /// Node name as a string
+    pub fn name_str(&self) -> &'a str {
+        core::str::from_utf8(self.name).unwrap_or("")
+    }
+
+    /// Name without the unit address
+    pub fn base_name(&self) -> &'a str {
+        let name = self.name_str();
+        name.split('@').next().unwrap_or(name)
+    }
+
+    /// Unit address parsed as hex (the part after '@')
+    pub fn unit_address(&self) -> Option<u64> {
+        let (_, addr) = self.name_str().split_once('@')?;
+        u64::from_str_radix(addr.split(',').next()?, 16).ok()
+    }
+
+    fn name_matches(&self, component: &str) -> bool {
+        if component.contains('@') {
+            self.name_str() == component
+        } else {
+            self.base_name() == component
+        }
+    }
+
+    /// Iterate over this node's properties
+    pub fn properties(&self) -> impl Iterator<Item = Property<'a>> + 'a {
+        let fdt = self.fdt;
+        let mut off = self.props_off;
+        core::iter::from_fn(move || loop {
+            let (tok, next) = fdt.token(off)?;
+            match tok {
+                Token::Prop(prop) => {
+                    off = next;
+                    return Some(prop);
+                }
+                Token::Nop => off = next,
+                _ => return None,
+            }
+        })
+    }
+
+    /// Look up a property by name
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|p| p.name == name.as_bytes())
+    }
+
+    /// Check the `compatible` string list
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.property("compatible")
+            .map(|p| p.strings().any(|s| s == compatible.as_bytes()))
+            .unwrap_or(false)
+    }
+
+    /// Check for a compatible string starting with `prefix`
+    pub fn is_compatible_prefix(&self, prefix: &str) -> bool {
+        self.property("compatible")
+            .map(|p| p.strings().any(|s| s.starts_with(prefix.as_bytes())))
+            .unwrap_or(false)
+    }
+
+    /// The node is enabled (no `status`, or "okay"/"ok")
+    pub fn is_enabled(&self) -> bool {
+        match self.property("status").and_then(|p| p.as_str()) {
+            None => true,
+            Some(status) => status == "okay" || status == "ok",
+        }
+    }
+
+    /// `#address-cells` for children (default 2)
+    pub fn address_cells(&self) -> u32 {
+        self.property("#address-cells").and_then(|p| p.cell(0)).unwrap_or(2)
+    }
+
+    /// `#size-cells` for children (default 1)
+    pub fn size_cells(&self) -> u32 {
+        self.property("#size-cells").and_then(|p| p.cell(0)).unwrap_or(1)
+    }
+
+    /// Iterate over `reg` as (address, size) using the parent's cell sizes
+    pub fn reg(&self, address_cells: u32, size_cells: u32) -> impl Iterator<Item = (u64, u64)> + 'a {
+        let value = self.property("reg").map(|p| p.value).unwrap_or(&[]);
+        let stride = ((address_cells + size_cells) * 4) as usize;
+        let read = move |off: usize, cells: u32| -> u64 {
+            (0..cells as usize).fold(0u64, |acc, i| {
+                acc << 32 | be32(value, off + i * 4).unwrap_or(0) as u64
+            })
+        };
+        (0..if stride == 0 { 0 } else { value.len() / stride }).map(move |i| {
+            let off = i * stride;
+            (read(off, address_cells), read(off + address_cells as usize * 4, size_cells))
+        })
+    }
+
+    /// Iterate over direct children
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> + 'a {
+        let depth = self.depth;
+        let mut iter = NodeIter { fdt: self.fdt, off: self.props_off, depth: depth + 1 };
+        core::iter::from_fn(move || loop {
+            let node = iter.next()?;
+            if node.depth == depth + 1 {
+                return Some(node);
+            }
+            if node.depth <= depth {
+                return None;
+            }
+        })
+    }
+}
+
+/// Depth-first node iterator
+pub struct NodeIter<'a> {
+    fdt: Fdt<'a>,
+    off: usize,
+    depth: usize,
+}
+
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        loop {
+            let (tok, next) = self.fdt.token(self.off)?;
+            self.off = next;
+            match tok {
+                Token::BeginNode(name) => {
+                    let node = Node { fdt: self.fdt, name, depth: self.depth, props_off: next };
+                    self.depth += 1;
+                    return Some(node);
+                }
+                Token::EndNode => {
+                    // An unbalanced END_NODE ends the iteration
+                    self.depth = self.depth.checked_sub(1)?;
+                }
+                Token::Prop(_) | Token::Nop => {}
+                Token::End => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Minimal DTB builder for tests
+    pub(crate) struct DtbBuilder {
+        structs: [u8; 2048],
+        slen: usize,
+        strings: [u8; 512],
+        strlen: usize,
+    }
+
+    impl DtbBuilder {
+        pub(crate) fn new() -> Self {
+            Self { structs: [0; 2048], slen: 0, strings: [0; 512], strlen: 0 }
+        }
+
+        fn put(&mut self, bytes: &[u8]) {
+            self.structs[self.slen..self.slen + bytes.len()].copy_from_slice(bytes);
+            self.slen = align4(self.slen + bytes.len());
+        }
+
+        fn string_off(&mut self, name: &str) -> u32 {
+            let off = self.strlen;
+            self.strings[off..off + name.len()].copy_from_slice(name.as_bytes());
+            self.strlen += name.len() + 1;
+            off as u32
+        }
+
+        pub(crate) fn begin(&mut self, name: &str) -> &mut Self {
+            self.put(&FDT_BEGIN_NODE.to_be_bytes());
+            let start = self.slen;
+            self.structs[start..start + name.len()].copy_from_slice(name.as_bytes());
+            self.slen = align4(start + name.len() + 1);
+            self
+        }
+
+        pub(crate) fn end(&mut self) -> &mut Self {
+            self.put(&FDT_END_NODE.to_be_bytes());
+            self
+        }
+
+        pub(crate) fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let nameoff = self.string_off(name);
+            self.put(&FDT_PROP.to_be_bytes());
+            self.put(&(value.len() as u32).to_be_bytes());
+            self.put(&nameoff.to_be_bytes());
+            if !value.is_empty() {
+                self.put(value);
+            }
+            self
+        }
+
+        pub(crate) fn prop_cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let mut buf = [0u8; 64];
+            for (i, c) in cells.iter().enumerate() {
+                buf[i * 4..i * 4 + 4].copy_from_slice(&c.to_be_bytes());
+            }
+            self.prop(name, &buf[..cells.len() * 4])
+        }
+
+        /// Serialize into `out`, returning the blob length
+        pub(crate) fn finish(&mut self, out: &mut [u8]) -> usize {
+            self.put(&FDT_END.to_be_bytes());
+
+            let rsvmap = FDT_HEADER_LEN;
+            let off_struct = rsvmap + 32;
+            let off_strings = off_struct + self.slen;
+            let total = off_strings + self.strlen;
+
+            let header = [
+                FDT_MAGIC, total as u32, off_struct as u32, off_strings as u32,
+                rsvmap as u32, 17, 16, 0, self.strlen as u32, self.slen as u32,
+            ];
+            for (i, v) in header.iter().enumerate() {
+                out[i * 4..i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+            }
+            // One reservation, then the terminator
+            out[rsvmap..rsvmap + 8].copy_from_slice(&0x4800_0000u64.to_be_bytes());
+            out[rsvmap + 8..rsvmap + 16].copy_from_slice(&0x1000u64.to_be_bytes());
+            out[off_struct..off_strings].copy_from_slice(&self.structs[..self.slen]);
+            out[off_strings..total].copy_from_slice(&self.strings[..self.strlen]);
+            total
+        }
+    }
+
+    fn sample(out: &mut [u8]) -> usize {
+        let mut b = DtbBuilder::new();
+        b.begin("")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .begin("chosen")
+            .prop("bootargs", b"console=ttyAMA0\0")
+            .end()
+            .begin("memory@40000000")
+            .prop("device_type", b"memory\0")
+            .prop_cells("reg", &[0, 0x4000_0000, 0, 0x4000_0000])
+            .end()
+            .begin("pl011@9000000")
+            .prop("compatible", b"arm,pl011\0arm,primecell\0")
+            .prop_cells("reg", &[0, 0x0900_0000, 0, 0x1000])
+            .end()
+            .end();
+        b.finish(out)
+    }
+
+    #[test]
+    fn test_parse_and_find() {
+        let mut buf = [0u8; 4096];
+        let len = sample(&mut buf);
+        let fdt = Fdt::parse(&buf[..len]).unwrap();
+
+        let chosen = fdt.find_node("/chosen").unwrap();
+        assert_eq!(chosen.property("bootargs").unwrap().as_str(), Some("console=ttyAMA0"));
+
+        let mem = fdt.find_node("/memory").unwrap();
+        assert_eq!(mem.unit_address(), Some(0x4000_0000));
+        let mut reg = mem.reg(2, 2);
+        assert_eq!(reg.next(), Some((0x4000_0000, 0x4000_0000)));
+        assert_eq!(reg.next(), None);
+
+        let uart = fdt.find_compatible("arm,primecell").unwrap();
+        assert_eq!(uart.base_name(), "pl011");
+        assert!(uart.is_enabled());
+    }
+
+    #[test]
+    fn test_children_and_reservations() {
+        let mut buf = [0u8; 4096];
+        let len = sample(&mut buf);
+        let fdt = Fdt::parse(&buf[..len]).unwrap();
+
+        let root = fdt.root().unwrap();
+        assert_eq!(root.children().count(), 3);
+
+        let mut rsv = fdt.reservations();
+        assert_eq!(rsv.next(), Some((0x4800_0000, 0x1000)));
+        assert_eq!(rsv.next(), None);
+    }
+
+    #[test]
+    fn test_rejects_bad_magic() {
+        let buf = [0u8; 64];
+        assert_eq!(Fdt::parse(&buf).err(), Some(RX_ERR_NOT_FOUND));
+    }
+}
@@ -0,0 +1,777 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Platform Discovery from the Device Tree
+//!
+//! Extracts the devices needed for early bring-up: memory, UARTs, the
+//! interrupt controller (GIC or PLIC/CLINT), timers and the PSCI conduit.
+//! `numa-node-id` properties on memory and CPU nodes are kept for the NUMA
+//! topology.
+//!
+//! # Notes
+//!
+//! Bus `ranges` translation is not performed; the `reg` addresses of
+//! nested nodes are assumed to be CPU physical addresses, which holds for
+//! QEMU virt and most SoCs with identity-mapped simple buses.
+
+use super::{Fdt, Node};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::Result;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Maximum memory ranges recorded
+pub const MAX_MEMORY_RANGES: usize = 16;
+
+/// Maximum reserved ranges recorded
+pub const MAX_RESERVED_RANGES: usize = 16;
+
+/// Maximum UARTs recorded
+pub const MAX_UARTS: usize = 4;
+
+/// Maximum CPUs whose `numa-node-id` is recorded
+pub const MAX_NUMA_CPUS: usize = 64;
+
+/// Maximum `/chosen/rng-seed` bytes kept
+pub const MAX_RNG_SEED: usize = 64;
+
+/// Maximum tree depth tracked for cell sizes
+const MAX_DEPTH: usize = 16;
+
+/// GIC interrupt specifier types
+const GIC_SPI: u32 = 0;
+const GIC_PPI: u32 = 1;
+
+/// First SPI / PPI interrupt IDs
+const GIC_SPI_BASE: u32 = 32;
+const GIC_PPI_BASE: u32 = 16;
+
+/// PSCI conduit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsciMethod {
+    /// Secure Monitor Call
+    Smc,
+
+    /// Hypervisor Call
+    Hvc,
+}
+
+/// UART type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartKind {
+    /// ARM PrimeCell PL011
+    Pl011,
+
+    /// 16550-compatible
+    Ns16550,
+}
+
+/// A discovered UART
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartInfo {
+    pub kind: UartKind,
+    pub base: u64,
+    pub size: u64,
+    pub irq: u32,
+}
+
+/// A discovered GIC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GicInfo {
+    /// GIC architecture version (2 or 3)
+    pub version: u32,
+
+    /// Distributor base
+    pub gicd_base: u64,
+
+    /// CPU interface (v2) or redistributor (v3) base
+    pub gicc_base: u64,
+
+    /// Size of the CPU interface / redistributor region
+    pub gicc_size: u64,
+}
+
+/// A discovered PLIC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlicInfo {
+    pub base: u64,
+    pub size: u64,
+
+    /// Number of interrupt sources (`riscv,ndev`)
+    pub ndev: u32,
+}
+
+/// A PCI address window translated by a host bridge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PciWindow {
+    /// Base address on the PCI bus
+    pub pci_base: u64,
+
+    /// Base address seen by the CPU
+    pub cpu_base: u64,
+
+    /// Window size
+    pub size: u64,
+}
+
+/// A generic ECAM PCIe host bridge (`pci-host-ecam-generic`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PciHostInfo {
+    /// ECAM window base (for `bus_start`)
+    pub ecam_base: u64,
+
+    /// ECAM window size
+    pub ecam_size: u64,
+
+    /// PCI segment (`linux,pci-domain`)
+    pub segment: u16,
+
+    /// First and last bus decoded
+    pub bus_start: u8,
+    pub bus_end: u8,
+
+    /// I/O port window
+    pub io: Option<PciWindow>,
+
+    /// 32-bit memory window
+    pub mmio32: Option<PciWindow>,
+
+    /// 64-bit memory window
+    pub mmio64: Option<PciWindow>,
+}
+
+/// ARM generic timer interrupts (GIC interrupt IDs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArmTimerInfo {
+    pub irq_sphys: u32,
+    pub irq_phys: u32,
+    pub irq_virt: u32,
+    pub irq_hyp: u32,
+
+    /// `clock-frequency` override (0 to use CNTFRQ)
+    pub freq: u32,
+}
+
+/// Platform description extracted from the device tree
+#[derive(Debug, Clone, Copy)]
+pub struct PlatformInfo {
+    memory: [(u64, u64); MAX_MEMORY_RANGES],
+    memory_node: [Option<u32>; MAX_MEMORY_RANGES],
+    memory_count: usize,
+
+    cpu_node: [(u64, u32); MAX_NUMA_CPUS],
+    cpu_node_count: usize,
+
+    reserved: [(u64, u64); MAX_RESERVED_RANGES],
+    reserved_count: usize,
+
+    uarts: [UartInfo; MAX_UARTS],
+    uart_count: usize,
+
+    /// Interrupt controller (ARM)
+    pub gic: Option<GicInfo>,
+
+    /// Interrupt controller (RISC-V)
+    pub plic: Option<PlicInfo>,
+
+    /// Core-local interruptor (RISC-V) as (base, size)
+    pub clint: Option<(u64, u64)>,
+
+    /// PCIe host bridge
+    pub pci_host: Option<PciHostInfo>,
+
+    /// ARM generic timer
+    pub arm_timer: Option<ArmTimerInfo>,
+
+    /// RISC-V timebase frequency from /cpus
+    pub timebase_frequency: u32,
+
+    /// PSCI conduit
+    pub psci: Option<PsciMethod>,
+
+    /// Number of enabled CPUs
+    pub cpu_count: u32,
+
+    /// Initial ramdisk as (start, end)
+    pub initrd: Option<(u64, u64)>,
+
+    /// Crash log region from a `ramoops` reserved-memory node as (base, size)
+    pub ramoops: Option<(u64, u64)>,
+
+    /// SBSA generic watchdog as (control frame, refresh frame)
+    pub sbsa_watchdog: Option<(u64, u64)>,
+
+    rng_seed: [u8; MAX_RNG_SEED],
+    rng_seed_len: usize,
+}
+
+impl PlatformInfo {
+    const EMPTY_UART: UartInfo = UartInfo { kind: UartKind::Pl011, base: 0, size: 0, irq: 0 };
+
+    /// Empty description
+    pub const fn new() -> Self {
+        Self {
+            memory: [(0, 0); MAX_MEMORY_RANGES],
+            memory_node: [None; MAX_MEMORY_RANGES],
+            memory_count: 0,
+            cpu_node: [(0, 0); MAX_NUMA_CPUS],
+            cpu_node_count: 0,
+            reserved: [(0, 0); MAX_RESERVED_RANGES],
+            reserved_count: 0,
+            uarts: [Self::EMPTY_UART; MAX_UARTS],
+            uart_count: 0,
+            gic: None,
+            plic: None,
+            clint: None,
+            pci_host: None,
+            arm_timer: None,
+            timebase_frequency: 0,
+            psci: None,
+            cpu_count: 0,
+            initrd: None,
+            ramoops: None,
+            sbsa_watchdog: None,
+            rng_seed: [0; MAX_RNG_SEED],
+            rng_seed_len: 0,
+        }
+    }
+
+    /// Random seed from the boot loader (`/chosen/rng-seed`)
+    pub fn rng_seed(&self) -> &[u8] {
+        &self.rng_seed[..self.rng_seed_len]
+    }
+
+    /// Memory ranges from /memory nodes
+    pub fn memory(&self) -> &[(u64, u64)] {
+        &self.memory[..self.memory_count]
+    }
+
+    /// Memory ranges whose node carries a `numa-node-id`, as
+    /// (base, size, node id)
+    pub fn memory_numa(&self) -> impl Iterator<Item = (u64, u64, u32)> + '_ {
+        self.memory()
+            .iter()
+            .zip(&self.memory_node)
+            .filter_map(|(&(base, size), node)| node.map(|node| (base, size, node)))
+    }
+
+    /// CPUs with a `numa-node-id`, as (hardware ID from `reg`, node id)
+    pub fn cpu_numa(&self) -> &[(u64, u32)] {
+        &self.cpu_node[..self.cpu_node_count]
+    }
+
+    /// Reserved ranges (reservation block and /reserved-memory)
+    pub fn reserved(&self) -> &[(u64, u64)] {
+        &self.reserved[..self.reserved_count]
+    }
+
+    /// Discovered UARTs
+    pub fn uarts(&self) -> &[UartInfo] {
+        &self.uarts[..self.uart_count]
+    }
+
+    fn push_memory(&mut self, base: u64, size: u64, node: Option<u32>) {
+        if size != 0 && self.memory_count < MAX_MEMORY_RANGES {
+            self.memory[self.memory_count] = (base, size);
+            self.memory_node[self.memory_count] = node;
+            self.memory_count += 1;
+        }
+    }
+
+    fn push_reserved(&mut self, base: u64, size: u64) {
+        if size != 0 && self.reserved_count < MAX_RESERVED_RANGES {
+            self.reserved[self.reserved_count] = (base, size);
+            self.reserved_count += 1;
+        }
+    }
+
+    fn push_uart(&mut self, uart: UartInfo) {
+        if self.uart_count < MAX_UARTS {
+            self.uarts[self.uart_count] = uart;
+            self.uart_count += 1;
+        }
+    }
+
+    /// Extract the platform description from a parsed tree
+    pub fn from_fdt(fdt: &Fdt) -> Self {
+        let mut info = Self::new();
+
+        for (base, size) in fdt.reservations() {
+            info.push_reserved(base, size);
+        }
+
+        // cells[d] holds (#address-cells, #size-cells) declared by the
+        // most recent node at depth d, i.e. the parent of depth d + 1
+        let mut cells = [(2u32, 1u32); MAX_DEPTH];
+        let mut parent_name: [&str; MAX_DEPTH] = [""; MAX_DEPTH];
+
+        for node in fdt.nodes() {
+            let depth = node.depth;
+            if depth >= MAX_DEPTH {
+                continue;
+            }
+            cells[depth] = (node.address_cells(), node.size_cells());
+            parent_name[depth] = node.base_name();
+
+            if depth == 0 || !node.is_enabled() {
+                continue;
+            }
+            let (ac, sc) = cells[depth - 1];
+            let parent = parent_name[depth - 1];
+
+            info.visit(&node, parent, ac, sc);
+        }
+
+        // The timer may carry its frequency on /cpus
+        if let Some(cpus) = fdt.find_node("/cpus") {
+            if let Some(freq) = cpus.property("timebase-frequency").and_then(|p| p.as_u64()) {
+                info.timebase_frequency = freq as u32;
+            }
+        }
+
+        if let Some(chosen) = fdt.find_node("/chosen") {
+            let start = chosen.property("linux,initrd-start").and_then(|p| p.as_u64());
+            let end = chosen.property("linux,initrd-end").and_then(|p| p.as_u64());
+            if let (Some(start), Some(end)) = (start, end) {
+                if end > start {
+                    info.initrd = Some((start, end));
+                }
+            }
+
+            if let Some(seed) = chosen.property("rng-seed") {
+                let len = seed.value.len().min(MAX_RNG_SEED);
+                info.rng_seed[..len].copy_from_slice(&seed.value[..len]);
+                info.rng_seed_len = len;
+            }
+        }
+
+        info
+    }
+
+    fn first_reg(node: &Node, ac: u32, sc: u32) -> (u64, u64) {
+        node.reg(ac, sc).next().unwrap_or((0, 0))
+    }
+
+    fn visit(&mut self, node: &Node, parent: &str, ac: u32, sc: u32) {
+        let device_type = node.property("device_type").and_then(|p| p.as_str());
+
+        let numa_node = node.property("numa-node-id").and_then(|p| p.cell(0));
+
+        if device_type == Some("memory") {
+            for (base, size) in node.reg(ac, sc) {
+                self.push_memory(base, size, numa_node);
+            }
+            return;
+        }
+
+        if parent == "reserved-memory" {
+            for (base, size) in node.reg(ac, sc) {
+                self.push_reserved(base, size);
+            }
+            if node.is_compatible("ramoops") {
+                self.ramoops = Some(Self::first_reg(node, ac, sc));
+            }
+            return;
+        }
+
+        if parent == "cpus" && device_type == Some("cpu") {
+            self.cpu_count += 1;
+            if let (Some(numa_node), Some((hw_id, _))) = (numa_node, node.reg(ac, sc).next()) {
+                if self.cpu_node_count < MAX_NUMA_CPUS {
+                    self.cpu_node[self.cpu_node_count] = (hw_id, numa_node);
+                    self.cpu_node_count += 1;
+                }
+            }
+            return;
+        }
+
+        if node.is_compatible("arm,pl011") {
+            let (base, size) = Self::first_reg(node, ac, sc);
+            self.push_uart(UartInfo { kind: UartKind::Pl011, base, size, irq: gic_irq(node, 0) });
+        } else if node.is_compatible("ns16550a") || node.is_compatible("ns16550") {
+            let (base, size) = Self::first_reg(node, ac, sc);
+            self.push_uart(UartInfo { kind: UartKind::Ns16550, base, size, irq: plain_irq(node) });
+        } else if node.is_compatible("arm,cortex-a15-gic") || node.is_compatible("arm,gic-400") {
+            let mut reg = node.reg(ac, sc);
+            let (gicd_base, _) = reg.next().unwrap_or((0, 0));
+            let (gicc_base, gicc_size) = reg.next().unwrap_or((0, 0));
+            self.gic = Some(GicInfo { version: 2, gicd_base, gicc_base, gicc_size });
+        } else if node.is_compatible("arm,gic-v3") {
+            let mut reg = node.reg(ac, sc);
+            let (gicd_base, _) = reg.next().unwrap_or((0, 0));
+            let (gicc_base, gicc_size) = reg.next().unwrap_or((0, 0));
+            self.gic = Some(GicInfo { version: 3, gicd_base, gicc_base, gicc_size });
+        } else if node.is_compatible("arm,armv8-timer") || node.is_compatible("arm,armv7-timer") {
+            self.arm_timer = Some(ArmTimerInfo {
+                irq_sphys: gic_irq(node, 0),
+                irq_phys: gic_irq(node, 1),
+                irq_virt: gic_irq(node, 2),
+                irq_hyp: gic_irq(node, 3),
+                freq: node.property("clock-frequency").and_then(|p| p.cell(0)).unwrap_or(0),
+            });
+        } else if node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0") {
+            let (base, size) = Self::first_reg(node, ac, sc);
+            let ndev = node.property("riscv,ndev").and_then(|p| p.cell(0)).unwrap_or(0);
+            self.plic = Some(PlicInfo { base, size, ndev });
+        } else if node.is_compatible("pci-host-ecam-generic") {
+            self.pci_host = Some(pci_host(node, ac, sc));
+        } else if node.is_compatible("riscv,clint0") || node.is_compatible("sifive,clint0") {
+            self.clint = Some(Self::first_reg(node, ac, sc));
+        } else if node.is_compatible("arm,sbsa-gwdt") {
+            let mut reg = node.reg(ac, sc);
+            let (control, _) = reg.next().unwrap_or((0, 0));
+            let (refresh, _) = reg.next().unwrap_or((0, 0));
+            self.sbsa_watchdog = Some((control, refresh));
+        } else if node.is_compatible_prefix("arm,psci") {
+            self.psci = match node.property("method").and_then(|p| p.as_str()) {
+                Some("hvc") => Some(PsciMethod::Hvc),
+                Some("smc") => Some(PsciMethod::Smc),
+                _ => None,
+            };
+        }
+    }
+}
+
+impl Default for PlatformInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// PCI `ranges` space codes (bits 24..25 of phys.hi)
+const PCI_SPACE_IO: u32 = 1;
+const PCI_SPACE_MEM32: u32 = 2;
+const PCI_SPACE_MEM64: u32 = 3;
+
+/// Read `count` cells starting at `index` as one big-endian number
+fn cells_u64(prop: &super::Property, index: usize, count: usize) -> Option<u64> {
+    let mut value = 0u64;
+    for i in 0..count {
+        value = (value << 32) | prop.cell(index + i)? as u64;
+    }
+    Some(value)
+}
+
+/// Decode a generic ECAM host bridge node
+///
+/// Each `ranges` entry is a 3-cell PCI address, a parent address of
+/// `ac` cells and a size of the node's own `#size-cells`.
+fn pci_host(node: &Node, ac: u32, sc: u32) -> PciHostInfo {
+    let (ecam_base, ecam_size) = node.reg(ac, sc).next().unwrap_or((0, 0));
+
+    let bus_range = node.property("bus-range");
+    let bus_start = bus_range.as_ref().and_then(|p| p.cell(0)).unwrap_or(0);
+    let bus_end = bus_range.as_ref().and_then(|p| p.cell(1)).unwrap_or(255);
+
+    let mut host = PciHostInfo {
+        ecam_base,
+        ecam_size,
+        segment: node.property("linux,pci-domain").and_then(|p| p.cell(0)).unwrap_or(0) as u16,
+        bus_start: bus_start.min(255) as u8,
+        bus_end: bus_end.min(255) as u8,
+        ..PciHostInfo::default()
+    };
+
+    let ranges = match node.property("ranges") {
+        Some(p) => p,
+        None => return host,
+    };
+    let pac = ac as usize;
+    let psc = node.size_cells() as usize;
+    let entry_cells = 3 + pac + psc;
+
+    let mut index = 0;
+    while let (Some(hi), Some(pci_base), Some(cpu_base), Some(size)) = (
+        ranges.cell(index),
+        cells_u64(&ranges, index + 1, 2),
+        cells_u64(&ranges, index + 3, pac),
+        cells_u64(&ranges, index + 3 + pac, psc),
+    ) {
+        let window = Some(PciWindow { pci_base, cpu_base, size });
+        match (hi >> 24) & 0x3 {
+            PCI_SPACE_IO => host.io = window,
+            PCI_SPACE_MEM32 => host.mmio32 = window,
+            PCI_SPACE_MEM64 => host.mmio64 = window,
+            _ => {}
+        }
+        index += entry_cells;
+    }
+
+    host
+}
+
+/// Decode the `index`-th 3-cell GIC interrupt specifier into an ID
+fn gic_irq(node: &Node, index: usize) -> u32 {
+    let prop = match node.property("interrupts") {
+        Some(p) => p,
+        None => return 0,
+    };
+
+    let kind = prop.cell(index * 3).unwrap_or(u32::MAX);
+    let num = prop.cell(index * 3 + 1).unwrap_or(0);
+    match kind {
+        GIC_SPI => num + GIC_SPI_BASE,
+        GIC_PPI => num + GIC_PPI_BASE,
+        _ => 0,
+    }
+}
+
+/// Decode a single-cell interrupt specifier (PLIC)
+fn plain_irq(node: &Node) -> u32 {
+    node.property("interrupts").and_then(|p| p.cell(0)).unwrap_or(0)
+}
+
+/// ============================================================================
+/// Global State
+/// ============================================================================
+
+/// Parsed platform description
+static PLATFORM: SpinMutex<PlatformInfo> = SpinMutex::new(PlatformInfo::new());
+
+/// Set once a device tree has been parsed
+static PLATFORM_READY: AtomicBool = AtomicBool::new(false);
+
+/// Parse the device tree at `paddr` into the global platform description
+///
+/// # Safety
+///
+/// `paddr` must point at readable memory holding a device tree.
+pub unsafe fn init_from_phys(paddr: u64) -> Result<()> {
+    let fdt = Fdt::from_phys(paddr)?;
+    let info = PlatformInfo::from_fdt(&fdt);
+
+    log_info!("FDT: {} bytes at {:#x}, {} CPUs", fdt.size(), paddr, info.cpu_count);
+    for (base, size) in info.memory() {
+        log_info!("  memory {:#x} +{:#x}", base, size);
+    }
+    for uart in info.uarts() {
+        log_info!("  uart {:?} at {:#x} irq {}", uart.kind, uart.base, uart.irq);
+    }
+    if let Some(gic) = &info.gic {
+        log_info!("  GICv{} gicd={:#x} gicc={:#x}", gic.version, gic.gicd_base, gic.gicc_base);
+    }
+    if let Some(plic) = &info.plic {
+        log_info!("  PLIC at {:#x} ({} sources)", plic.base, plic.ndev);
+    }
+    if let Some(host) = &info.pci_host {
+        log_info!("  PCIe ECAM at {:#x} buses {}-{}", host.ecam_base, host.bus_start, host.bus_end);
+    }
+    match info.psci {
+        Some(method) => {
+            log_info!("  PSCI via {:?}", method);
+        }
+        None => {
+            log_warn!("  no PSCI node");
+        }
+    }
+
+    *PLATFORM.lock() = info;
+    PLATFORM_READY.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Get a copy of the platform description, if a device tree was parsed
+pub fn platform_info() -> Option<PlatformInfo> {
+    if PLATFORM_READY.load(Ordering::Acquire) {
+        Some(*PLATFORM.lock())
+    } else {
+        None
+    }
+}
+
+/// Parse the device tree passed by the bootloader, if any
+pub fn init() {
+    let paddr = match crate::kernel::handoff::get().and_then(|h| h.devicetree()) {
+        Some(paddr) => paddr,
+        None => {
+            log_info!("FDT: no device tree from boot loader");
+            return;
+        }
+    };
+
+    if let Err(err) = unsafe { init_from_phys(paddr) } {
+        log_warn!("FDT: initialization failed: {}", err);
+    }
+}
+
+/// Bring up PSCI, the GIC, the console UART and the generic timer from
+/// the parsed device tree
+///
+/// Devices missing from the tree are left to their built-in defaults.
+#[cfg(target_arch = "aarch64")]
+pub fn platform_init() {
+    let info = match platform_info() {
+        Some(info) => info,
+        None => return,
+    };
+
+    if let Some(method) = info.psci {
+        crate::kernel::dev::psci::init(method == PsciMethod::Hvc, [0; 3], [0; 3], [0; 3], [0; 3]);
+    }
+
+    match info.gic {
+        Some(gic) if gic.version == 2 => {
+            let gicc_offset = gic.gicc_base.wrapping_sub(gic.gicd_base);
+            let result = unsafe {
+                crate::kernel::dev::interrupt::gicv2::platform_init(gic.gicd_base, 0, gicc_offset, 0, 0, 0)
+            };
+            if let Err(err) = result {
+                log_warn!("FDT: GICv2 init failed: {}", err);
+            }
+        }
+        Some(gic) => {
+            log_warn!("FDT: GICv{} not supported yet", gic.version);
+        }
+        None => {}
+    }
+
+    if let Some(uart) = info.uarts().iter().find(|u| u.kind == UartKind::Pl011) {
+        crate::kernel::dev::uart::pl011::pl011_platform_init(uart.base, uart.irq);
+    }
+
+    if let Some(timer) = info.arm_timer {
+        let result = crate::kernel::dev::timer::arm_generic::platform_init(
+            timer.irq_phys,
+            timer.irq_virt,
+            timer.irq_sphys,
+            timer.freq,
+        );
+        if let Err(err) = result {
+            log_warn!("FDT: generic timer init failed: {}", err);
+        }
+    }
+}
+
+/// Bring up the PLIC and the timer frequency from the parsed device tree
+///
+/// Without a `timebase-frequency` the timer keeps QEMU virt's 10 MHz.
+#[cfg(target_arch = "riscv64")]
+pub fn platform_init() {
+    use crate::kernel::arch::riscv64::{arch::arch_curr_hartid, plic, timer};
+
+    let info = match platform_info() {
+        Some(info) => info,
+        None => return,
+    };
+
+    if let Some(p) = info.plic {
+        unsafe {
+            plic::plic_init(p.base as usize);
+            plic::plic_set_threshold(arch_curr_hartid() as u32, 0);
+        }
+    }
+
+    if info.timebase_frequency != 0 {
+        timer::riscv_timer_set_frequency(info.timebase_frequency as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::fdt::tests::DtbBuilder;
+
+    fn qemu_virt_like(out: &mut [u8]) -> usize {
+        let mut b = DtbBuilder::new();
+        b.begin("")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .begin("psci")
+            .prop("compatible", b"arm,psci-1.0\0arm,psci-0.2\0")
+            .prop("method", b"hvc\0")
+            .end()
+            .begin("memory@40000000")
+            .prop("device_type", b"memory\0")
+            .prop_cells("reg", &[0, 0x4000_0000, 0, 0x8000_0000])
+            .prop_cells("numa-node-id", &[0])
+            .end()
+            .begin("cpus")
+            .prop_cells("#address-cells", &[1])
+            .prop_cells("#size-cells", &[0])
+            .begin("cpu@0")
+            .prop("device_type", b"cpu\0")
+            .prop_cells("reg", &[0])
+            .end()
+            .begin("cpu@1")
+            .prop("device_type", b"cpu\0")
+            .prop_cells("reg", &[1])
+            .prop_cells("numa-node-id", &[1])
+            .end()
+            .end()
+            .begin("intc@8000000")
+            .prop("compatible", b"arm,cortex-a15-gic\0")
+            .prop_cells("reg", &[0, 0x0800_0000, 0, 0x1_0000, 0, 0x0801_0000, 0, 0x1_0000])
+            .end()
+            .begin("timer")
+            .prop("compatible", b"arm,armv8-timer\0")
+            .prop_cells("interrupts", &[1, 13, 0x104, 1, 14, 0x104, 1, 11, 0x104, 1, 10, 0x104])
+            .end()
+            .begin("pl011@9000000")
+            .prop("compatible", b"arm,pl011\0arm,primecell\0")
+            .prop_cells("reg", &[0, 0x0900_0000, 0, 0x1000])
+            .prop_cells("interrupts", &[0, 1, 4])
+            .end()
+            .begin("pcie@10000000")
+            .prop("compatible", b"pci-host-ecam-generic\0")
+            .prop_cells("#address-cells", &[3])
+            .prop_cells("#size-cells", &[2])
+            .prop_cells("reg", &[0x40, 0x1000_0000, 0, 0x1000_0000])
+            .prop_cells("bus-range", &[0, 0xff])
+            .prop_cells("ranges", &[
+                0x0100_0000, 0, 0, 0, 0x3eff_0000, 0, 0x1_0000,
+                0x0200_0000, 0, 0x1000_0000, 0, 0x1000_0000, 0, 0x2eff_0000,
+            ])
+            .end()
+            .begin("disabled-uart@9100000")
+            .prop("compatible", b"arm,pl011\0")
+            .prop("status", b"disabled\0")
+            .end()
+            .begin("chosen")
+            .prop("rng-seed", &[0x5e, 0xed, 1, 2, 3, 4, 5, 6])
+            .end()
+            .end();
+        b.finish(out)
+    }
+
+    #[test]
+    fn test_platform_from_fdt() {
+        let mut buf = [0u8; 4096];
+        let len = qemu_virt_like(&mut buf);
+        let fdt = Fdt::parse(&buf[..len]).unwrap();
+        let info = PlatformInfo::from_fdt(&fdt);
+
+        assert_eq!(info.psci, Some(PsciMethod::Hvc));
+        assert_eq!(info.memory(), &[(0x4000_0000, 0x8000_0000)]);
+        assert_eq!(info.reserved(), &[(0x4800_0000, 0x1000)]);
+        assert_eq!(info.cpu_count, 2);
+        assert!(info.memory_numa().eq([(0x4000_0000, 0x8000_0000, 0)]));
+        assert_eq!(info.cpu_numa(), &[(1, 1)]);
+
+        let gic = info.gic.unwrap();
+        assert_eq!(gic.version, 2);
+        assert_eq!(gic.gicd_base, 0x0800_0000);
+        assert_eq!(gic.gicc_base, 0x0801_0000);
+
+        let timer = info.arm_timer.unwrap();
+        assert_eq!(timer.irq_phys, 30);
+        assert_eq!(timer.irq_virt, 27);
+
+        let host = info.pci_host.unwrap();
+        assert_eq!(host.ecam_base, 0x40_1000_0000);
+        assert_eq!(host.bus_end, 0xff);
+        assert_eq!(host.io.unwrap().cpu_base, 0x3eff_0000);
+        assert_eq!(host.mmio32.unwrap().size, 0x2eff_0000);
+        assert!(host.mmio64.is_none());
+
+        assert_eq!(info.uarts().len(), 1);
+        assert_eq!(info.uarts()[0].base, 0x0900_0000);
+        assert_eq!(info.uarts()[0].irq, 33);
+
+        assert_eq!(info.rng_seed(), &[0x5e, 0xed, 1, 2, 3, 4, 5, 6]);
+    }
+}
@@ -28,7 +28,7 @@
 //! ```
 
 use crate::kernel::dev::intel_rng;
-use crate::kernel::debug::hexdump_ex;
+use crate::kernel::debug::hexdump;
 use crate::{log_info, log_warn};
 
 /// Generate and print a random 32-bit unsigned integer
@@ -73,7 +73,7 @@ pub fn cmd_rng(count: usize, wait: bool) {
         debug_assert!(done <= todo, "HW RNG returned more bytes than requested");
 
         if done > 0 {
-            hexdump_ex(buf.as_ptr(), done, offset);
+            hexdump(&buf[..done], offset as u64);
             offset += done;
         }
 
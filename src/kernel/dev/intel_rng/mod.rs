@@ -13,6 +13,7 @@
 //!
 //! - **RDSEED**: True hardware random number generator (NIST SP 800-90B/C compliant)
 //! - **RDRAND**: Hardware random number generator (fallback for older CPUs)
+//! - **RNDR**: The Armv8.5 random number register, used on arm64
 //! - **Blocking/Non-blocking modes**: Control behavior when entropy is unavailable
 //!
 //! # Usage
@@ -36,6 +37,7 @@ use crate::kernel::arch::amd64::feature;
 enum EntropyInstr {
     RdSeed,
     RdRand,
+    Rndr,
 }
 
 impl EntropyInstr {
@@ -50,6 +52,7 @@ impl EntropyInstr {
             let success = match self {
                 EntropyInstr::RdRand => _rdrand64_step(&mut val),
                 EntropyInstr::RdSeed => _rdseed64_step(&mut val),
+                EntropyInstr::Rndr => 0,
             };
 
             if success == 1 {
@@ -60,9 +63,37 @@ impl EntropyInstr {
         }
     }
 
-    /// Non-x86_64 platforms always fail
+    /// Read RNDR; the CPU clears NZCV.Z on success
     #[inline(always)]
-    #[cfg(not(target_arch = "x86_64"))]
+    #[cfg(target_arch = "aarch64")]
+    fn step(self) -> Option<u64> {
+        if self != EntropyInstr::Rndr {
+            return None;
+        }
+
+        let val: u64;
+        let ok: u64;
+        unsafe {
+            // RNDR is s3_3_c2_c4_0; older assemblers lack the name
+            core::arch::asm!(
+                "mrs {val}, s3_3_c2_c4_0",
+                "cset {ok}, ne",
+                val = out(reg) val,
+                ok = out(reg) ok,
+                options(nomem, nostack),
+            );
+        }
+
+        if ok != 0 {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    /// Other platforms always fail
+    #[inline(always)]
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
     fn step(self) -> Option<u64> {
         None
     }
@@ -156,7 +187,18 @@ fn get_entropy_from_cpu(buf: &mut [u8], block: bool) -> isize {
         return -1; // RX_ERR_NOT_SUPPORTED
     }
 
-    #[cfg(not(target_arch = "x86_64"))]
+    #[cfg(target_arch = "aarch64")]
+    {
+        use crate::kernel::arch::arm64::{feature::arm64_feature_test, ARM64_FEATURE_ISA_RNDR};
+
+        if arm64_feature_test(ARM64_FEATURE_ISA_RNDR) {
+            return get_entropy_from_instruction(buf, block, EntropyInstr::Rndr) as isize;
+        }
+
+        -1 // RX_ERR_NOT_SUPPORTED
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
     {
         let _ = block;
         -1 // RX_ERR_NOT_SUPPORTED
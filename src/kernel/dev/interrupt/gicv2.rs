@@ -414,6 +414,9 @@ pub fn handle_irq() -> (u32, bool) {
             return (vector, true);
         }
 
+        crate::kernel::lib::crypto::entropy::add_interrupt_jitter(vector);
+        crate::kernel::irq::record_vector(vector);
+
         // End of interrupt
         gicc_write(GICC_EOIR, iar);
 
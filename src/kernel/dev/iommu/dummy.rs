@@ -0,0 +1,64 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Dummy IOMMU
+//!
+//! For machines without remapping hardware, or with it left disabled:
+//! every bus transaction ID is valid and device addresses are physical
+//! addresses. Nothing stops a device from reaching other memory.
+
+use super::Iommu;
+use crate::rustux::types::{PAddr, Result};
+use alloc::vec::Vec;
+
+/// Identity-mapping IOMMU
+#[derive(Debug, Default)]
+pub struct DummyIommu;
+
+impl DummyIommu {
+    /// Create a dummy IOMMU
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Iommu for DummyIommu {
+    fn name(&self) -> &'static str {
+        "dummy"
+    }
+
+    fn is_valid_bus_txn_id(&self, _bus_txn_id: u64) -> bool {
+        true
+    }
+
+    fn translates(&self) -> bool {
+        false
+    }
+
+    fn map(&self, _bus_txn_id: u64, pages: &[PAddr], _perms: u32) -> Result<Vec<u64>> {
+        Ok(pages.to_vec())
+    }
+
+    fn unmap(&self, _bus_txn_id: u64, _dev_addr: u64, _pages: usize) -> Result {
+        Ok(())
+    }
+
+    fn clear_mappings(&self, _bus_txn_id: u64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::iommu::perm;
+
+    #[test]
+    fn test_dummy_is_identity() {
+        let iommu = DummyIommu::new();
+        assert!(iommu.is_valid_bus_txn_id(0x1234));
+        assert!(!iommu.translates());
+        assert_eq!(iommu.map(0, &[0x5000, 0x9000], perm::READ), Ok(alloc::vec![0x5000, 0x9000]));
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Intel VT-d
+//!
+//! Drives the remapping units the DMAR lists. Each unit gets a root
+//! table with a context table per bus, allocated as devices show up;
+//! each device gets its own domain (domain ID and second-level page
+//! table), so no two devices share an IO address space.
+//!
+//! Invalidation uses the register interface (no queued invalidation):
+//! global context-cache invalidation when a context entry changes, and
+//! domain-selective IOTLB invalidation when pages are unmapped.
+//! Interrupt remapping and fault reporting are not set up; a blocked
+//! transaction is just dropped by the hardware.
+
+use super::{bus_txn_rid, bus_txn_segment, IoAddressSpace, Iommu, PteFormat};
+use crate::kernel::dev::acpi::{self, Dmar, DrhdUnit};
+use crate::kernel::mmu;
+use crate::kernel::pmm;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::{PAddr, Result};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, Ordering};
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Register offsets
+const REG_CAP: usize = 0x08;
+const REG_ECAP: usize = 0x10;
+const REG_GCMD: usize = 0x18;
+const REG_GSTS: usize = 0x1C;
+const REG_RTADDR: usize = 0x20;
+const REG_CCMD: usize = 0x28;
+
+/// Global command / status bits
+const GCMD_TE: u32 = 1 << 31;
+const GCMD_SRTP: u32 = 1 << 30;
+
+/// GSTS bits that are states rather than one-shot commands, written back
+/// with every command
+const GSTS_PERSISTENT: u32 = 0x96FF_FFFF;
+
+/// Capability bits
+const CAP_CM: u64 = 1 << 7;
+const CAP_SAGAW_4LEVEL: u64 = 1 << 10;
+
+/// Extended capability bits
+const ECAP_C: u64 = 1 << 0;
+
+/// Context command bits
+const CCMD_ICC: u64 = 1 << 63;
+const CCMD_CIRG_GLOBAL: u64 = 1 << 61;
+
+/// IOTLB invalidate bits
+const IOTLB_IVT: u64 = 1 << 63;
+const IOTLB_IIRG_GLOBAL: u64 = 1 << 60;
+const IOTLB_IIRG_DOMAIN: u64 = 2 << 60;
+
+/// Root and context entry bits
+const ENTRY_PRESENT: u64 = 1 << 0;
+const CONTEXT_AW_48BIT: u64 = 2;
+
+/// Register polls before giving up
+const POLL_LIMIT: u32 = 1_000_000;
+
+/// One remapping unit
+struct Unit {
+    /// Kernel address of the registers
+    regs: usize,
+
+    /// Offset of the IOTLB invalidate register
+    iotlb: usize,
+
+    /// Caching mode: not-present entries may be cached too
+    caching_mode: bool,
+
+    /// Number of domain IDs
+    domains: u32,
+
+    /// Root table, one entry per bus
+    root_table: PAddr,
+
+    /// Context table of each bus, 0 until a device on it is attached
+    context_tables: [PAddr; 256],
+}
+
+impl Unit {
+    fn read32(&self, off: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.regs + off) as *const u32) }
+    }
+
+    fn read64(&self, off: usize) -> u64 {
+        unsafe { core::ptr::read_volatile((self.regs + off) as *const u64) }
+    }
+
+    fn write32(&self, off: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.regs + off) as *mut u32, value) }
+    }
+
+    fn write64(&self, off: usize, value: u64) {
+        unsafe { core::ptr::write_volatile((self.regs + off) as *mut u64, value) }
+    }
+
+    fn poll(&self, done: impl Fn(&Self) -> bool) -> Result {
+        for _ in 0..POLL_LIMIT {
+            if done(self) {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(RX_ERR_TIMED_OUT)
+    }
+
+    /// Issue a global command and wait for its status bit to become `on`
+    fn command(&self, bit: u32, on: bool) -> Result {
+        let status = self.read32(REG_GSTS) & GSTS_PERSISTENT;
+        self.write32(REG_GCMD, if on { status | bit } else { status & !bit });
+        self.poll(|unit| (unit.read32(REG_GSTS) & bit != 0) == on)
+    }
+
+    /// Map the unit's registers and enable translation with an empty
+    /// root table, which blocks all DMA through the unit
+    fn init(drhd: &DrhdUnit) -> Result<Self> {
+        let regs = mmu::phys_to_virt(drhd.base);
+        let probe = Self { regs, iotlb: 0, caching_mode: false, domains: 0, root_table: 0, context_tables: [0; 256] };
+        let cap = probe.read64(REG_CAP);
+        let ecap = probe.read64(REG_ECAP);
+
+        if cap & CAP_SAGAW_4LEVEL == 0 {
+            log_warn!("VT-d: unit at {:#x} lacks 4-level paging", drhd.base);
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+        if ecap & ECAP_C == 0 {
+            log_warn!("VT-d: unit at {:#x} does not snoop page walks", drhd.base);
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        let unit = Self {
+            iotlb: ((ecap >> 8) & 0x3FF) as usize * 16 + 8,
+            caching_mode: cap & CAP_CM != 0,
+            domains: 1 << (4 + 2 * (cap & 0x7)),
+            root_table: pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?,
+            ..probe
+        };
+
+        unit.write64(REG_RTADDR, unit.root_table);
+        unit.command(GCMD_SRTP, true)?;
+        unit.invalidate_context()?;
+        unit.invalidate_iotlb(IOTLB_IIRG_GLOBAL)?;
+        unit.command(GCMD_TE, true)?;
+        Ok(unit)
+    }
+
+    fn invalidate_context(&self) -> Result {
+        self.write64(REG_CCMD, CCMD_ICC | CCMD_CIRG_GLOBAL);
+        self.poll(|unit| unit.read64(REG_CCMD) & CCMD_ICC == 0)
+    }
+
+    fn invalidate_iotlb(&self, granularity: u64) -> Result {
+        self.write64(self.iotlb, IOTLB_IVT | granularity);
+        self.poll(|unit| unit.read64(unit.iotlb) & IOTLB_IVT == 0)
+    }
+
+    fn invalidate_domain(&self, domain: u16) -> Result {
+        self.invalidate_iotlb(IOTLB_IIRG_DOMAIN | (domain as u64) << 32)
+    }
+
+    fn table(paddr: PAddr) -> &'static mut [u64; 512] {
+        unsafe { &mut *(pmm::paddr_to_vaddr(paddr) as *mut [u64; 512]) }
+    }
+
+    /// Point requester `rid` at a second-level table in `domain`, or
+    /// block it
+    fn set_context(&mut self, rid: u16, target: Option<(PAddr, u16)>) -> Result {
+        let bus = (rid >> 8) as usize;
+        let devfn = (rid & 0xFF) as usize;
+
+        if self.context_tables[bus] == 0 {
+            if target.is_none() {
+                return Ok(());
+            }
+            let table = pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?;
+            self.context_tables[bus] = table;
+            // Root entries are 16 bytes; the context table pointer is in
+            // the low half
+            Self::table(self.root_table)[bus * 2] = table | ENTRY_PRESENT;
+        }
+
+        // Context entries are 16 bytes: table pointer low, domain high.
+        // The present bit goes last on the way in and first on the way out.
+        let entries = Self::table(self.context_tables[bus]);
+        match target {
+            Some((root, domain)) => {
+                entries[devfn * 2 + 1] = CONTEXT_AW_48BIT | (domain as u64) << 8;
+                fence(Ordering::SeqCst);
+                entries[devfn * 2] = root | ENTRY_PRESENT;
+            }
+            None => {
+                entries[devfn * 2] = 0;
+                fence(Ordering::SeqCst);
+                entries[devfn * 2 + 1] = 0;
+            }
+        }
+        fence(Ordering::SeqCst);
+
+        self.invalidate_context()?;
+        self.invalidate_iotlb(IOTLB_IIRG_GLOBAL)
+    }
+}
+
+/// A device attached to a unit
+struct Device {
+    unit: usize,
+    domain: u16,
+    aspace: IoAddressSpace,
+}
+
+/// Intel VT-d driver
+pub struct IntelIommu {
+    dmar: Dmar,
+    units: SpinMutex<Vec<Unit>>,
+    devices: SpinMutex<BTreeMap<u64, Device>>,
+    next_domain: SpinMutex<u16>,
+}
+
+impl IntelIommu {
+    /// Take over every unit in the DMAR
+    ///
+    /// Fails if there is no DMAR or any unit cannot be used; units
+    /// already enabled keep blocking DMA.
+    fn init() -> Result<Self> {
+        let dmar = acpi::dmar().ok_or(RX_ERR_NOT_FOUND)?;
+        if dmar.units().is_empty() {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+
+        let mut units = Vec::new();
+        for drhd in dmar.units() {
+            units.push(Unit::init(drhd)?);
+            log_info!("VT-d: unit at {:#x} segment {} enabled", drhd.base, drhd.segment);
+        }
+
+        Ok(Self {
+            dmar,
+            units: SpinMutex::new(units),
+            devices: SpinMutex::new(BTreeMap::new()),
+            // Domain 0 is reserved in caching mode; skip it everywhere
+            next_domain: SpinMutex::new(1),
+        })
+    }
+
+    fn unit_for(&self, bus_txn_id: u64) -> Option<usize> {
+        if bus_txn_id >> 32 != 0 {
+            return None;
+        }
+        self.dmar.unit_for(bus_txn_segment(bus_txn_id), bus_txn_rid(bus_txn_id))
+    }
+
+    /// Attach `bus_txn_id` to a fresh domain
+    fn attach(&self, bus_txn_id: u64) -> Result<Device> {
+        let unit = self.unit_for(bus_txn_id).ok_or(RX_ERR_INVALID_ARGS)?;
+        let mut units = self.units.lock();
+
+        let domain = {
+            let mut next = self.next_domain.lock();
+            if *next as u32 >= units[unit].domains {
+                return Err(RX_ERR_NO_RESOURCES);
+            }
+            *next += 1;
+            *next - 1
+        };
+        let aspace = IoAddressSpace::new(PteFormat::VtdSecondLevel)?;
+        units[unit].set_context(bus_txn_rid(bus_txn_id), Some((aspace.root(), domain)))?;
+        Ok(Device { unit, domain, aspace })
+    }
+}
+
+impl Iommu for IntelIommu {
+    fn name(&self) -> &'static str {
+        "intel-vtd"
+    }
+
+    fn is_valid_bus_txn_id(&self, bus_txn_id: u64) -> bool {
+        self.unit_for(bus_txn_id).is_some()
+    }
+
+    fn translates(&self) -> bool {
+        true
+    }
+
+    fn map(&self, bus_txn_id: u64, pages: &[PAddr], perms: u32) -> Result<Vec<u64>> {
+        let mut devices = self.devices.lock();
+        if !devices.contains_key(&bus_txn_id) {
+            devices.insert(bus_txn_id, self.attach(bus_txn_id)?);
+        }
+        let device = devices.get_mut(&bus_txn_id).ok_or(RX_ERR_INTERNAL)?;
+
+        let base = device.aspace.map(pages, perms)?;
+        let units = self.units.lock();
+        if units[device.unit].caching_mode {
+            units[device.unit].invalidate_domain(device.domain)?;
+        }
+        Ok((0..pages.len() as u64).map(|i| base + i * super::page_table::IO_PAGE_SIZE).collect())
+    }
+
+    fn unmap(&self, bus_txn_id: u64, dev_addr: u64, pages: usize) -> Result {
+        let mut devices = self.devices.lock();
+        let device = devices.get_mut(&bus_txn_id).ok_or(RX_ERR_NOT_FOUND)?;
+        device.aspace.unmap(dev_addr, pages);
+        self.units.lock()[device.unit].invalidate_domain(device.domain)
+    }
+
+    fn clear_mappings(&self, bus_txn_id: u64) {
+        let Some(device) = self.devices.lock().remove(&bus_txn_id) else {
+            return;
+        };
+        let mut units = self.units.lock();
+        if let Err(err) = units[device.unit].set_context(bus_txn_rid(bus_txn_id), None) {
+            log_warn!("VT-d: detaching {:#x} failed: {}", bus_txn_id, err);
+        }
+        // The page tables go with `device` now the hardware is off them
+    }
+}
+
+/// The VT-d driver, started on first use
+static INTEL: SpinMutex<Option<Arc<IntelIommu>>> = SpinMutex::new(None);
+
+/// Get the VT-d driver, enabling the hardware the first time
+pub fn get_or_init() -> Result<Arc<IntelIommu>> {
+    let mut intel = INTEL.lock();
+    if let Some(iommu) = intel.as_ref() {
+        return Ok(iommu.clone());
+    }
+    let iommu = Arc::new(IntelIommu::init()?);
+    *intel = Some(iommu.clone());
+    Ok(iommu)
+}
+
+/// Whether VT-d translation has been enabled
+pub fn is_enabled() -> bool {
+    INTEL.lock().is_some()
+}
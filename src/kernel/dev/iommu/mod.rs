@@ -0,0 +1,107 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! IOMMU Drivers
+//!
+//! An IOMMU translates the addresses devices put on the bus, so a device
+//! can only reach memory a driver pinned for it. Each device, named by
+//! its bus transaction ID, gets its own IO address space; pinned pages
+//! are mapped there at consecutive device addresses.
+//!
+//! # Drivers
+//!
+//! - [`intel`] - Intel VT-d, from the ACPI DMAR
+//! - [`smmu`] - Arm SMMUv3, from the ACPI IORT
+//! - [`dummy`] - No translation: device addresses are physical addresses
+//!
+//! Once a translating IOMMU is enabled, DMA from a device is blocked until
+//! a BTI (bus transaction initiator) is created for it and pages pinned.
+//! BTIs are in [`object::bti`](crate::kernel::object::bti).
+//!
+//! # Bus Transaction IDs
+//!
+//! A PCI device's ID is `segment << 16 | requester ID`, where the
+//! requester ID is `bus << 8 | dev << 3 | func`.
+
+pub mod dummy;
+pub mod intel;
+pub mod page_table;
+pub mod smmu;
+
+pub use dummy::DummyIommu;
+pub use intel::IntelIommu;
+pub use page_table::{IoAddressSpace, IoPageTable, IovaAllocator, PteFormat};
+pub use smmu::SmmuV3;
+
+use crate::rustux::types::{PAddr, Result};
+use alloc::vec::Vec;
+
+/// Mapping permissions, the same bits as the BTI pin options
+pub mod perm {
+    /// Device may read
+    pub const READ: u32 = 0x01;
+
+    /// Device may write
+    pub const WRITE: u32 = 0x02;
+
+    /// Device may execute (where the hardware distinguishes it)
+    pub const EXECUTE: u32 = 0x04;
+}
+
+/// An IOMMU driver
+pub trait Iommu: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Whether the IOMMU translates `bus_txn_id`'s transactions
+    ///
+    /// BTIs can only be created for valid IDs.
+    fn is_valid_bus_txn_id(&self, bus_txn_id: u64) -> bool;
+
+    /// Whether device addresses differ from physical addresses
+    fn translates(&self) -> bool;
+
+    /// Map `pages` for `bus_txn_id` and return the device address of
+    /// each
+    ///
+    /// Translating IOMMUs return consecutive addresses.
+    fn map(&self, bus_txn_id: u64, pages: &[PAddr], perms: u32) -> Result<Vec<u64>>;
+
+    /// Unmap `pages` pages starting at device address `dev_addr`, as
+    /// returned by [`map`](Self::map)
+    fn unmap(&self, bus_txn_id: u64, dev_addr: u64, pages: usize) -> Result;
+
+    /// Drop every mapping and the IO address space of `bus_txn_id`
+    fn clear_mappings(&self, bus_txn_id: u64);
+}
+
+/// Bus transaction ID of a PCI device
+pub const fn pci_bus_txn_id(segment: u16, rid: u16) -> u64 {
+    (segment as u64) << 16 | rid as u64
+}
+
+/// PCI segment of a bus transaction ID
+pub const fn bus_txn_segment(bus_txn_id: u64) -> u16 {
+    (bus_txn_id >> 16) as u16
+}
+
+/// PCI requester ID of a bus transaction ID
+pub const fn bus_txn_rid(bus_txn_id: u64) -> u16 {
+    bus_txn_id as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bus_txn_id() {
+        let id = pci_bus_txn_id(1, 0x0310);
+        assert_eq!(id, 0x1_0310);
+        assert_eq!(bus_txn_segment(id), 1);
+        assert_eq!(bus_txn_rid(id), 0x0310);
+    }
+}
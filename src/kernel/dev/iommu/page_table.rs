@@ -0,0 +1,419 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! IO Page Tables and Address Spaces
+//!
+//! VT-d second-level tables and SMMUv3 stage-2 tables are both four
+//! levels of 512 entries over a 48-bit address with 4 KiB pages; only the
+//! entry bits differ, which [`PteFormat`] encodes. Tables are PMM pages.
+//! VMX extended page tables share the layout, so guests use
+//! [`IoPageTable`] for their guest-physical memory too.
+//!
+//! Table writes are not flushed from the CPU caches, so the IOMMU must
+//! snoop them (VT-d ECAP.C, SMMU IDR0.COHACC); the drivers refuse
+//! hardware that does not.
+
+use super::perm;
+use crate::kernel::pmm;
+use crate::rustux::types::err::*;
+use crate::rustux::types::{PAddr, Result};
+use alloc::vec::Vec;
+
+/// Page size of IO mappings
+pub const IO_PAGE_SIZE: u64 = 4096;
+
+/// Bits of device address translated
+pub const IO_ADDRESS_BITS: u32 = 48;
+
+/// Entries per table
+const ENTRIES: usize = 512;
+
+/// Output address bits of an entry
+const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// VT-d second-level entry bits
+const VTD_READ: u64 = 1 << 0;
+const VTD_WRITE: u64 = 1 << 1;
+
+/// EPT entry bits
+const EPT_READ: u64 = 1 << 0;
+const EPT_WRITE: u64 = 1 << 1;
+const EPT_EXECUTE: u64 = 1 << 2;
+const EPT_MEMTYPE_WB: u64 = 6 << 3;
+
+/// Arm stage-2 descriptor bits
+const S2_VALID: u64 = 1 << 0;
+const S2_TABLE_OR_PAGE: u64 = 1 << 1;
+const S2_MEMATTR_NORMAL_WB: u64 = 0b1111 << 2;
+const S2AP_READ: u64 = 1 << 6;
+const S2AP_WRITE: u64 = 1 << 7;
+const S2_SH_INNER: u64 = 0b11 << 8;
+const S2_AF: u64 = 1 << 10;
+const S2_XN: u64 = 1 << 54;
+
+/// Entry encoding of an IO page table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PteFormat {
+    /// Intel VT-d second-level paging
+    VtdSecondLevel,
+
+    /// Arm VMSAv8-64 stage 2, 4 KiB granule
+    ArmStage2,
+
+    /// Intel VMX extended page tables, mapping write-back memory
+    Ept,
+}
+
+impl PteFormat {
+    /// Entry pointing at a next-level table
+    pub const fn table(self, paddr: u64) -> u64 {
+        match self {
+            // Non-leaf entries must grant everything a leaf below may
+            Self::VtdSecondLevel => paddr & ADDR_MASK | VTD_READ | VTD_WRITE,
+            Self::ArmStage2 => paddr & ADDR_MASK | S2_VALID | S2_TABLE_OR_PAGE,
+            Self::Ept => paddr & ADDR_MASK | EPT_READ | EPT_WRITE | EPT_EXECUTE,
+        }
+    }
+
+    /// Entry mapping one page with `perms`
+    pub const fn page(self, paddr: u64, perms: u32) -> u64 {
+        let addr = paddr & ADDR_MASK;
+        match self {
+            Self::VtdSecondLevel => {
+                let mut pte = addr;
+                if perms & perm::READ != 0 {
+                    pte |= VTD_READ;
+                }
+                if perms & perm::WRITE != 0 {
+                    pte |= VTD_WRITE;
+                }
+                pte
+            }
+            Self::ArmStage2 => {
+                let mut pte = addr | S2_VALID | S2_TABLE_OR_PAGE | S2_MEMATTR_NORMAL_WB | S2_SH_INNER | S2_AF;
+                if perms & perm::READ != 0 {
+                    pte |= S2AP_READ;
+                }
+                if perms & perm::WRITE != 0 {
+                    pte |= S2AP_WRITE;
+                }
+                if perms & perm::EXECUTE == 0 {
+                    pte |= S2_XN;
+                }
+                pte
+            }
+            Self::Ept => {
+                let mut pte = addr | EPT_MEMTYPE_WB;
+                if perms & perm::READ != 0 {
+                    pte |= EPT_READ;
+                }
+                if perms & perm::WRITE != 0 {
+                    pte |= EPT_WRITE;
+                }
+                if perms & perm::EXECUTE != 0 {
+                    pte |= EPT_EXECUTE;
+                }
+                pte
+            }
+        }
+    }
+
+    /// Whether an entry is in use
+    pub const fn is_present(self, pte: u64) -> bool {
+        match self {
+            Self::VtdSecondLevel => pte & (VTD_READ | VTD_WRITE) != 0,
+            Self::ArmStage2 => pte & S2_VALID != 0,
+            Self::Ept => pte & (EPT_READ | EPT_WRITE | EPT_EXECUTE) != 0,
+        }
+    }
+
+    /// Address an entry points at
+    pub const fn address(pte: u64) -> u64 {
+        pte & ADDR_MASK
+    }
+}
+
+/// ============================================================================
+/// Page Table
+/// ============================================================================
+
+/// A four-level IO page table
+pub struct IoPageTable {
+    format: PteFormat,
+    root: PAddr,
+}
+
+impl IoPageTable {
+    /// Empty table
+    pub fn new(format: PteFormat) -> Result<Self> {
+        let root = pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?;
+        Ok(Self { format, root })
+    }
+
+    /// Physical address of the top-level table
+    pub fn root(&self) -> PAddr {
+        self.root
+    }
+
+    fn entries(table: PAddr) -> &'static mut [u64; ENTRIES] {
+        unsafe { &mut *(pmm::paddr_to_vaddr(table) as *mut [u64; ENTRIES]) }
+    }
+
+    const fn index(iova: u64, level: u32) -> usize {
+        ((iova >> (12 + 9 * level)) & (ENTRIES as u64 - 1)) as usize
+    }
+
+    /// Last-level entry for `iova`, allocating tables on the way if
+    /// `create` is set
+    fn leaf(&mut self, iova: u64, create: bool) -> Result<Option<&'static mut u64>> {
+        let mut table = self.root;
+        for level in (1..4).rev() {
+            let entry = &mut Self::entries(table)[Self::index(iova, level)];
+            if !self.format.is_present(*entry) {
+                if !create {
+                    return Ok(None);
+                }
+                let next = pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?;
+                *entry = self.format.table(next);
+            }
+            table = PteFormat::address(*entry);
+        }
+        Ok(Some(&mut Self::entries(table)[Self::index(iova, 0)]))
+    }
+
+    /// Map the page at `iova` to `paddr`
+    ///
+    /// Fails with `RX_ERR_ALREADY_EXISTS` if `iova` is mapped.
+    pub fn map_page(&mut self, iova: u64, paddr: PAddr, perms: u32) -> Result {
+        if iova >> IO_ADDRESS_BITS != 0 {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        let format = self.format;
+        let entry = self.leaf(iova, true)?.ok_or(RX_ERR_INTERNAL)?;
+        if format.is_present(*entry) {
+            return Err(RX_ERR_ALREADY_EXISTS);
+        }
+        *entry = format.page(paddr, perms);
+        Ok(())
+    }
+
+    /// Unmap the page at `iova`, returning what it mapped
+    ///
+    /// Tables emptied by the unmap are kept until the table is dropped.
+    pub fn unmap_page(&mut self, iova: u64) -> Option<PAddr> {
+        let entry = self.leaf(iova, false).ok().flatten()?;
+        if !self.format.is_present(*entry) {
+            return None;
+        }
+        let paddr = PteFormat::address(*entry);
+        *entry = 0;
+        Some(paddr)
+    }
+
+    fn free_table(format: PteFormat, table: PAddr, level: u32) {
+        if level > 0 {
+            for &entry in Self::entries(table).iter() {
+                if format.is_present(entry) {
+                    Self::free_table(format, PteFormat::address(entry), level - 1);
+                }
+            }
+        }
+        pmm::pmm_free_page(table);
+    }
+}
+
+impl Drop for IoPageTable {
+    fn drop(&mut self) {
+        Self::free_table(self.format, self.root, 3);
+    }
+}
+
+/// ============================================================================
+/// Device Address Allocation
+/// ============================================================================
+
+/// Hands out ranges of device addresses, first fit
+///
+/// Address 0 is never handed out, so a null pointer in a DMA descriptor
+/// always faults.
+#[derive(Debug)]
+pub struct IovaAllocator {
+    /// End of the address space
+    limit: u64,
+
+    /// Start of the never-allocated tail
+    next: u64,
+
+    /// Freed ranges as (base, pages), sorted by base
+    free: Vec<(u64, u64)>,
+}
+
+impl IovaAllocator {
+    /// Allocator over `[IO_PAGE_SIZE, limit)`
+    pub const fn new(limit: u64) -> Self {
+        Self { limit, next: IO_PAGE_SIZE, free: Vec::new() }
+    }
+
+    /// Allocate `pages` consecutive pages
+    pub fn alloc(&mut self, pages: u64) -> Result<u64> {
+        if pages == 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if let Some(pos) = self.free.iter().position(|&(_, len)| len >= pages) {
+            let (base, len) = self.free[pos];
+            if len == pages {
+                self.free.remove(pos);
+            } else {
+                self.free[pos] = (base + pages * IO_PAGE_SIZE, len - pages);
+            }
+            return Ok(base);
+        }
+
+        let size = pages.checked_mul(IO_PAGE_SIZE).ok_or(RX_ERR_NO_RESOURCES)?;
+        if self.limit - self.next < size {
+            return Err(RX_ERR_NO_RESOURCES);
+        }
+        let base = self.next;
+        self.next += size;
+        Ok(base)
+    }
+
+    /// Give back `pages` pages at `base`
+    pub fn free(&mut self, base: u64, pages: u64) {
+        let pos = self.free.partition_point(|&(b, _)| b < base);
+        self.free.insert(pos, (base, pages));
+
+        // Merge with the following and preceding ranges
+        if pos + 1 < self.free.len() && base + pages * IO_PAGE_SIZE == self.free[pos + 1].0 {
+            self.free[pos].1 += self.free.remove(pos + 1).1;
+        }
+        if pos > 0 {
+            let (prev, len) = self.free[pos - 1];
+            if prev + len * IO_PAGE_SIZE == base {
+                self.free[pos - 1].1 += self.free.remove(pos).1;
+            }
+        }
+
+        // Fold a free range ending at the tail back into it
+        if let Some(&(last, len)) = self.free.last() {
+            if last + len * IO_PAGE_SIZE == self.next {
+                self.next = last;
+                self.free.pop();
+            }
+        }
+    }
+}
+
+/// ============================================================================
+/// Address Space
+/// ============================================================================
+
+/// One device's IO address space
+pub struct IoAddressSpace {
+    table: IoPageTable,
+    iova: IovaAllocator,
+}
+
+impl IoAddressSpace {
+    /// Empty address space
+    pub fn new(format: PteFormat) -> Result<Self> {
+        Ok(Self {
+            table: IoPageTable::new(format)?,
+            iova: IovaAllocator::new(1 << IO_ADDRESS_BITS),
+        })
+    }
+
+    /// Physical address of the top-level table
+    pub fn root(&self) -> PAddr {
+        self.table.root()
+    }
+
+    /// Map `pages` at consecutive device addresses, returning the first
+    pub fn map(&mut self, pages: &[PAddr], perms: u32) -> Result<u64> {
+        let base = self.iova.alloc(pages.len() as u64)?;
+        for (i, &paddr) in pages.iter().enumerate() {
+            if let Err(err) = self.table.map_page(base + i as u64 * IO_PAGE_SIZE, paddr, perms) {
+                for j in 0..i as u64 {
+                    self.table.unmap_page(base + j * IO_PAGE_SIZE);
+                }
+                self.iova.free(base, pages.len() as u64);
+                return Err(err);
+            }
+        }
+        Ok(base)
+    }
+
+    /// Unmap `pages` pages at `base` and free the addresses
+    pub fn unmap(&mut self, base: u64, pages: usize) {
+        for i in 0..pages as u64 {
+            self.table.unmap_page(base + i * IO_PAGE_SIZE);
+        }
+        self.iova.free(base, pages as u64);
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pte_formats() {
+        let vtd = PteFormat::VtdSecondLevel;
+        assert_eq!(vtd.page(0x1234_5000, perm::READ), 0x1234_5001);
+        assert_eq!(vtd.table(0x8000), 0x8003);
+        assert!(!vtd.is_present(0x1234_5000));
+
+        let s2 = PteFormat::ArmStage2;
+        let pte = s2.page(0x1234_5000, perm::READ | perm::WRITE);
+        assert_eq!(PteFormat::address(pte), 0x1234_5000);
+        assert_ne!(pte & S2AP_WRITE, 0);
+        assert_ne!(pte & S2_XN, 0);
+        assert_eq!(s2.page(0x1000, perm::READ | perm::EXECUTE) & S2_XN, 0);
+        assert_eq!(s2.table(0x8000), 0x8003);
+
+        let ept = PteFormat::Ept;
+        assert_eq!(ept.page(0x1234_5000, perm::READ | perm::EXECUTE), 0x1234_5035);
+        assert_eq!(ept.page(0x1234_5000, perm::READ | perm::WRITE), 0x1234_5033);
+        assert_eq!(ept.table(0x8000), 0x8007);
+        assert!(!ept.is_present(0x1234_5030));
+    }
+
+    #[test]
+    fn test_index() {
+        let iova = 0x0000_8040_2010_3000u64;
+        assert_eq!(IoPageTable::index(iova, 0), 0x103);
+        assert_eq!(IoPageTable::index(iova, 1), 0x100);
+        assert_eq!(IoPageTable::index(iova, 2), 0x100);
+        assert_eq!(IoPageTable::index(iova, 3), 0x100);
+    }
+
+    #[test]
+    fn test_iova_allocator() {
+        let mut iova = IovaAllocator::new(16 * IO_PAGE_SIZE);
+        let a = iova.alloc(2).unwrap();
+        let b = iova.alloc(4).unwrap();
+        let c = iova.alloc(1).unwrap();
+        assert_eq!(a, IO_PAGE_SIZE);
+        assert_eq!(b, 3 * IO_PAGE_SIZE);
+        assert_eq!(c, 7 * IO_PAGE_SIZE);
+
+        // First fit reuses a freed hole
+        iova.free(b, 4);
+        assert_eq!(iova.alloc(3).unwrap(), b);
+        assert_eq!(iova.alloc(1).unwrap(), b + 3 * IO_PAGE_SIZE);
+
+        // Too big for what is left
+        assert_eq!(iova.alloc(9), Err(RX_ERR_NO_RESOURCES));
+
+        // Freeing the tail gives it back whole
+        iova.free(c, 1);
+        assert_eq!(iova.alloc(8).unwrap(), c);
+    }
+}
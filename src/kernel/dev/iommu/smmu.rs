@@ -0,0 +1,397 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Arm SMMUv3
+//!
+//! Drives the SMMUs the IORT lists, translating PCI devices with stage 2
+//! only: each stream gets a stream table entry pointing at its own
+//! stage-2 table, tagged with its own VMID.
+//!
+//! The stream table is two-level with 64 entries per second-level table,
+//! so each level is one page; stream IDs are limited to 15 bits. Stream
+//! table entries start out invalid, which aborts the stream's
+//! transactions. Configuration and TLB invalidation go through the
+//! command queue. The event queue and fault interrupts are not set up.
+
+use super::{bus_txn_rid, bus_txn_segment, IoAddressSpace, Iommu, PteFormat};
+use crate::kernel::dev::acpi::{self, Iort};
+use crate::kernel::mmu;
+use crate::kernel::pmm;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::{PAddr, Result};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, Ordering};
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Register offsets
+const REG_IDR0: usize = 0x00;
+const REG_IDR1: usize = 0x04;
+const REG_CR0: usize = 0x20;
+const REG_CR0ACK: usize = 0x24;
+const REG_CR1: usize = 0x28;
+const REG_STRTAB_BASE: usize = 0x80;
+const REG_STRTAB_BASE_CFG: usize = 0x88;
+const REG_CMDQ_BASE: usize = 0x90;
+const REG_CMDQ_PROD: usize = 0x98;
+const REG_CMDQ_CONS: usize = 0x9C;
+
+/// IDR0 bits
+const IDR0_S2P: u32 = 1 << 0;
+const IDR0_COHACC: u32 = 1 << 4;
+const IDR0_VMID16: u32 = 1 << 18;
+const IDR0_ST_LEVEL_2: u32 = 1 << 27;
+
+/// CR0 bits
+const CR0_SMMUEN: u32 = 1 << 0;
+const CR0_CMDQEN: u32 = 1 << 3;
+
+/// CR1: queues and tables write-back cacheable, inner shareable
+const CR1_CACHEABLE: u32 = 1 | 1 << 2 | 3 << 4 | 1 << 6 | 1 << 8 | 3 << 10;
+
+/// Base register read-allocate hint
+const BASE_RA: u64 = 1 << 62;
+
+/// Stream table geometry
+const STRTAB_SPLIT: u32 = 6;
+const MAX_SID_BITS: u32 = 15;
+const STRTAB_FMT_2LEVEL: u32 = 1 << 16;
+const STE_WORDS: usize = 8;
+
+/// Stream table entry bits
+const STE_V: u64 = 1 << 0;
+const STE_CONFIG_S2: u64 = 0b110 << 1;
+const STE_S2T0SZ_48BIT: u64 = 16 << 32;
+const STE_S2SL0_LEVEL0: u64 = 2 << 38;
+const STE_S2IR0_WBWA: u64 = 1 << 40;
+const STE_S2OR0_WBWA: u64 = 1 << 42;
+const STE_S2SH0_INNER: u64 = 3 << 44;
+const STE_S2PS_48BIT: u64 = 5 << 48;
+const STE_S2AA64: u64 = 1 << 51;
+const STE_S2TTB_MASK: u64 = 0x000F_FFFF_FFFF_FFF0;
+
+/// Command opcodes
+const CMD_CFGI_STE: u64 = 0x03;
+const CMD_CFGI_ALL: u64 = 0x04;
+const CMD_TLBI_S12_VMALL: u64 = 0x28;
+const CMD_TLBI_NSNH_ALL: u64 = 0x30;
+const CMD_SYNC: u64 = 0x46;
+
+/// Command queue entries, log2 (one page of 16-byte commands)
+const CMDQ_LOG2: u32 = 8;
+
+/// Register polls before giving up
+const POLL_LIMIT: u32 = 1_000_000;
+
+/// One SMMU
+struct Instance {
+    /// Kernel address of register page 0
+    regs: usize,
+
+    /// Stream ID bits in use
+    sid_bits: u32,
+
+    /// VMIDs available
+    vmids: u32,
+
+    /// First-level stream table
+    l1: PAddr,
+
+    /// Second-level tables, 0 until a stream in their span is attached
+    l2: Vec<PAddr>,
+
+    /// Command queue and its entry count log2
+    cmdq: PAddr,
+    cmdq_log2: u32,
+
+    /// Producer index, with the wrap bit
+    prod: u32,
+}
+
+impl Instance {
+    fn read32(&self, off: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.regs + off) as *const u32) }
+    }
+
+    fn write32(&self, off: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.regs + off) as *mut u32, value) }
+    }
+
+    fn write64(&self, off: usize, value: u64) {
+        unsafe { core::ptr::write_volatile((self.regs + off) as *mut u64, value) }
+    }
+
+    fn poll(&self, done: impl Fn(&Self) -> bool) -> Result {
+        for _ in 0..POLL_LIMIT {
+            if done(self) {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(RX_ERR_TIMED_OUT)
+    }
+
+    fn set_cr0(&self, value: u32) -> Result {
+        self.write32(REG_CR0, value);
+        self.poll(|smmu| smmu.read32(REG_CR0ACK) == value)
+    }
+
+    fn page(paddr: PAddr) -> &'static mut [u64; 512] {
+        unsafe { &mut *(pmm::paddr_to_vaddr(paddr) as *mut [u64; 512]) }
+    }
+
+    /// Set up the stream table and command queue and enable the SMMU,
+    /// which aborts every stream until one is attached
+    fn init(base: u64) -> Result<Self> {
+        let regs = mmu::phys_to_virt(base);
+        let mut smmu = Self { regs, sid_bits: 0, vmids: 0, l1: 0, l2: Vec::new(), cmdq: 0, cmdq_log2: 0, prod: 0 };
+
+        let idr0 = smmu.read32(REG_IDR0);
+        let idr1 = smmu.read32(REG_IDR1);
+        if idr0 & IDR0_S2P == 0 || idr0 & IDR0_ST_LEVEL_2 == 0 {
+            log_warn!("SMMUv3: {:#x} lacks stage 2 or two-level stream tables", base);
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+        if idr0 & IDR0_COHACC == 0 {
+            log_warn!("SMMUv3: {:#x} does not snoop table walks", base);
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        smmu.sid_bits = (idr1 & 0x3F).clamp(STRTAB_SPLIT, MAX_SID_BITS);
+        smmu.vmids = if idr0 & IDR0_VMID16 != 0 { 1 << 16 } else { 1 << 8 };
+        smmu.cmdq_log2 = ((idr1 >> 21) & 0x1F).min(CMDQ_LOG2);
+        smmu.l1 = pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?;
+        smmu.l2 = alloc::vec![0; 1 << (smmu.sid_bits - STRTAB_SPLIT)];
+        smmu.cmdq = pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?;
+
+        smmu.set_cr0(0)?;
+        smmu.write32(REG_CR1, CR1_CACHEABLE);
+        smmu.write64(REG_STRTAB_BASE, smmu.l1 | BASE_RA);
+        smmu.write32(REG_STRTAB_BASE_CFG, smmu.sid_bits | STRTAB_SPLIT << 6 | STRTAB_FMT_2LEVEL);
+        smmu.write64(REG_CMDQ_BASE, smmu.cmdq | BASE_RA | smmu.cmdq_log2 as u64);
+        smmu.write32(REG_CMDQ_PROD, 0);
+        smmu.write32(REG_CMDQ_CONS, 0);
+        smmu.set_cr0(CR0_CMDQEN)?;
+
+        smmu.submit([CMD_CFGI_ALL, 31])?;
+        smmu.submit([CMD_TLBI_NSNH_ALL, 0])?;
+        smmu.sync()?;
+        smmu.set_cr0(CR0_CMDQEN | CR0_SMMUEN)?;
+        Ok(smmu)
+    }
+
+    /// Queue one command
+    fn submit(&mut self, cmd: [u64; 2]) -> Result {
+        let mask = (1 << self.cmdq_log2) - 1;
+        let wrap = 1 << self.cmdq_log2;
+
+        // Full when the indices match but the wrap bits differ
+        let prod = self.prod;
+        self.poll(|smmu| {
+            let cons = smmu.read32(REG_CMDQ_CONS);
+            cons & mask != prod & mask || (cons ^ prod) & wrap == 0
+        })?;
+
+        let slot = (self.prod & mask) as usize * 2;
+        let queue = Self::page(self.cmdq);
+        queue[slot] = cmd[0];
+        queue[slot + 1] = cmd[1];
+        fence(Ordering::SeqCst);
+
+        self.prod = (self.prod + 1) & (wrap * 2 - 1);
+        self.write32(REG_CMDQ_PROD, self.prod);
+        Ok(())
+    }
+
+    /// Wait for every queued command to complete
+    fn sync(&mut self) -> Result {
+        self.submit([CMD_SYNC, 0])?;
+        let prod = self.prod;
+        let wrap_mask = (2 << self.cmdq_log2) - 1;
+        self.poll(|smmu| smmu.read32(REG_CMDQ_CONS) & wrap_mask == prod)
+    }
+
+    /// Point stream `sid` at a stage-2 table tagged `vmid`, or abort it
+    fn set_stream(&mut self, sid: u32, target: Option<(PAddr, u16)>) -> Result {
+        let group = (sid >> STRTAB_SPLIT) as usize;
+        if self.l2[group] == 0 {
+            if target.is_none() {
+                return Ok(());
+            }
+            let table = pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY)?;
+            self.l2[group] = table;
+            // SPAN is log2 of the entries plus one
+            Self::page(self.l1)[group] = table | (STRTAB_SPLIT + 1) as u64;
+        }
+
+        let index = (sid & ((1 << STRTAB_SPLIT) - 1)) as usize * STE_WORDS;
+        let ste = &mut Self::page(self.l2[group])[index..index + STE_WORDS];
+        match target {
+            Some((root, vmid)) => {
+                ste[1] = 0;
+                ste[2] = vmid as u64
+                    | STE_S2T0SZ_48BIT
+                    | STE_S2SL0_LEVEL0
+                    | STE_S2IR0_WBWA
+                    | STE_S2OR0_WBWA
+                    | STE_S2SH0_INNER
+                    | STE_S2PS_48BIT
+                    | STE_S2AA64;
+                ste[3] = root & STE_S2TTB_MASK;
+                fence(Ordering::SeqCst);
+                ste[0] = STE_V | STE_CONFIG_S2;
+            }
+            None => ste[0] = 0,
+        }
+        fence(Ordering::SeqCst);
+
+        // Not a leaf invalidation: the first-level descriptor may be new
+        self.submit([CMD_CFGI_STE | (sid as u64) << 32, 0])?;
+        self.sync()
+    }
+
+    fn invalidate_vmid(&mut self, vmid: u16) -> Result {
+        self.submit([CMD_TLBI_S12_VMALL | (vmid as u64) << 32, 0])?;
+        self.sync()
+    }
+}
+
+/// A stream attached to an SMMU
+struct Stream {
+    smmu: usize,
+    sid: u32,
+    vmid: u16,
+    aspace: IoAddressSpace,
+}
+
+/// Arm SMMUv3 driver
+pub struct SmmuV3 {
+    iort: Iort,
+    smmus: SpinMutex<Vec<Instance>>,
+    streams: SpinMutex<BTreeMap<u64, Stream>>,
+    next_vmid: SpinMutex<u32>,
+}
+
+impl SmmuV3 {
+    /// Take over every SMMU in the IORT
+    fn init() -> Result<Self> {
+        let iort = acpi::iort().ok_or(RX_ERR_NOT_FOUND)?;
+        if iort.smmus().is_empty() {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+
+        let mut smmus = Vec::new();
+        for smmu in iort.smmus() {
+            let instance = Instance::init(smmu.base)?;
+            log_info!("SMMUv3: {:#x} enabled, {} stream ID bits", smmu.base, instance.sid_bits);
+            smmus.push(instance);
+        }
+
+        Ok(Self {
+            iort,
+            smmus: SpinMutex::new(smmus),
+            streams: SpinMutex::new(BTreeMap::new()),
+            // VMID 0 is left unused so a zeroed entry never aliases a stream
+            next_vmid: SpinMutex::new(1),
+        })
+    }
+
+    /// SMMU and stream ID of `bus_txn_id`, if the SMMU can translate it
+    fn stream_for(&self, bus_txn_id: u64) -> Option<(usize, u32)> {
+        if bus_txn_id >> 32 != 0 {
+            return None;
+        }
+        let (smmu, sid) = self.iort.stream_id(bus_txn_segment(bus_txn_id) as u32, bus_txn_rid(bus_txn_id))?;
+        let sid_bits = self.smmus.lock().get(smmu)?.sid_bits;
+        (sid >> sid_bits == 0).then_some((smmu, sid))
+    }
+
+    /// Attach `bus_txn_id` to a fresh stage-2 table
+    fn attach(&self, bus_txn_id: u64) -> Result<Stream> {
+        let (smmu, sid) = self.stream_for(bus_txn_id).ok_or(RX_ERR_INVALID_ARGS)?;
+        let mut smmus = self.smmus.lock();
+
+        let vmid = {
+            let mut next = self.next_vmid.lock();
+            if *next >= smmus[smmu].vmids {
+                return Err(RX_ERR_NO_RESOURCES);
+            }
+            *next += 1;
+            (*next - 1) as u16
+        };
+        let aspace = IoAddressSpace::new(PteFormat::ArmStage2)?;
+        smmus[smmu].set_stream(sid, Some((aspace.root(), vmid)))?;
+        Ok(Stream { smmu, sid, vmid, aspace })
+    }
+}
+
+impl Iommu for SmmuV3 {
+    fn name(&self) -> &'static str {
+        "arm-smmuv3"
+    }
+
+    fn is_valid_bus_txn_id(&self, bus_txn_id: u64) -> bool {
+        self.stream_for(bus_txn_id).is_some()
+    }
+
+    fn translates(&self) -> bool {
+        true
+    }
+
+    fn map(&self, bus_txn_id: u64, pages: &[PAddr], perms: u32) -> Result<Vec<u64>> {
+        let mut streams = self.streams.lock();
+        if !streams.contains_key(&bus_txn_id) {
+            streams.insert(bus_txn_id, self.attach(bus_txn_id)?);
+        }
+        let stream = streams.get_mut(&bus_txn_id).ok_or(RX_ERR_INTERNAL)?;
+
+        // Invalid entries are never cached, so new mappings need no
+        // invalidation
+        let base = stream.aspace.map(pages, perms)?;
+        Ok((0..pages.len() as u64).map(|i| base + i * super::page_table::IO_PAGE_SIZE).collect())
+    }
+
+    fn unmap(&self, bus_txn_id: u64, dev_addr: u64, pages: usize) -> Result {
+        let mut streams = self.streams.lock();
+        let stream = streams.get_mut(&bus_txn_id).ok_or(RX_ERR_NOT_FOUND)?;
+        stream.aspace.unmap(dev_addr, pages);
+        self.smmus.lock()[stream.smmu].invalidate_vmid(stream.vmid)
+    }
+
+    fn clear_mappings(&self, bus_txn_id: u64) {
+        let Some(stream) = self.streams.lock().remove(&bus_txn_id) else {
+            return;
+        };
+        let mut smmus = self.smmus.lock();
+        let smmu = &mut smmus[stream.smmu];
+        if let Err(err) = smmu.set_stream(stream.sid, None).and_then(|()| smmu.invalidate_vmid(stream.vmid)) {
+            log_warn!("SMMUv3: detaching {:#x} failed: {}", bus_txn_id, err);
+        }
+    }
+}
+
+/// The SMMUv3 driver, started on first use
+static SMMU: SpinMutex<Option<Arc<SmmuV3>>> = SpinMutex::new(None);
+
+/// Get the SMMUv3 driver, enabling the hardware the first time
+pub fn get_or_init() -> Result<Arc<SmmuV3>> {
+    let mut smmu = SMMU.lock();
+    if let Some(iommu) = smmu.as_ref() {
+        return Ok(iommu.clone());
+    }
+    let iommu = Arc::new(SmmuV3::init()?);
+    *smmu = Some(iommu.clone());
+    Ok(iommu)
+}
+
+/// Whether SMMUv3 translation has been enabled
+pub fn is_enabled() -> bool {
+    SMMU.lock().is_some()
+}
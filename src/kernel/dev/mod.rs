@@ -23,6 +23,12 @@ pub mod timer;
 // PCIe bus driver
 pub mod pcie;
 
+// ACPI table parser
+pub mod acpi;
+
+// Device tree parser
+pub mod fdt;
+
 // Hardware Random Number Generator
 pub mod hw_rng;
 
@@ -32,9 +38,21 @@ pub mod intel_rng;
 // Userspace display (framebuffer)
 pub mod udisplay;
 
+// Early framebuffer console
+pub mod fbcon;
+
 // ARM PSCI (Power State Coordination Interface)
 pub mod psci;
 
+// IOMMU drivers (VT-d, SMMUv3)
+pub mod iommu;
+
+// Hardware watchdogs (i6300ESB, SBSA)
+pub mod watchdog;
+
+// TPM 2.0 (FIFO, CRB)
+pub mod tpm;
+
 // Re-exports
 pub use uart::*;
 pub use interrupt::*;
@@ -0,0 +1,528 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PCI Bus Driver
+//!
+//! Enumerates every function behind the platform ECAM windows, assigns
+//! BARs that firmware left unprogrammed and keeps the resulting device
+//! list for the PCI syscalls and the `lspci` command.
+//!
+//! # Design
+//!
+//! ECAM windows come from `platform_ecam_regions()` (ACPI MCFG or the
+//! device tree host bridge). Enumeration scans every bus in a window
+//! rather than following bridges. BARs are assigned from the host bridge
+//! windows described by the device tree; on ACPI platforms firmware has
+//! already assigned them. Bridge windows are not programmed, so BAR
+//! assignment only covers functions on the root bus.
+//!
+//! Devices are identified by their index in enumeration order.
+//!
+//! # Usage
+//!
+//! ```rust
+//! bus::init();
+//! bus::with_device(0, |dev| dev.set_bus_master(true))?;
+//! ```
+
+use crate::kernel::dev::fdt::platform::PciWindow;
+use crate::kernel::dev::pcie::config::*;
+use crate::kernel::dev::pcie::constants::*;
+use crate::kernel::dev::pcie::device::*;
+use crate::kernel::dev::pcie::ecam::*;
+use crate::kernel::dev::pcie::msi::*;
+use crate::kernel::dev::pcie::{platform_ecam_regions, PciAddrSpace};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::*;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Lowest I/O port handed out, keeping clear of legacy ISA ports
+const PIO_ALLOC_FLOOR: u64 = 0x1000;
+
+/// ============================================================================
+/// BAR Windows
+/// ============================================================================
+
+/// Bump allocator over one host bridge window
+#[derive(Debug, Clone, Copy)]
+pub struct BarWindow {
+    window: PciWindow,
+
+    /// Next free PCI bus address
+    next: u64,
+}
+
+impl BarWindow {
+    /// Allocate from `window`, never below PCI address `floor`
+    pub fn new(window: PciWindow, floor: u64) -> Self {
+        Self { window, next: window.pci_base.max(floor) }
+    }
+
+    /// Allocate `size` bytes aligned to `size`
+    ///
+    /// Returns the (PCI bus address, CPU physical address) pair.
+    pub fn alloc(&mut self, size: u64) -> Option<(u64, u64)> {
+        if size == 0 || !size.is_power_of_two() {
+            return None;
+        }
+        let base = self.next.checked_add(size - 1)? & !(size - 1);
+        let end = base.checked_add(size)?;
+        if end > self.window.pci_base + self.window.size {
+            return None;
+        }
+        self.next = end;
+        Some((base, base - self.window.pci_base + self.window.cpu_base))
+    }
+}
+
+/// ============================================================================
+/// Devices
+/// ============================================================================
+
+/// An enumerated PCI function
+#[derive(Debug, Clone)]
+pub struct BusDevice {
+    /// Identification, BARs and legacy interrupt
+    pub info: PciDevice,
+
+    /// Physical ECAM base for bus 0 of the device's segment
+    ecam: usize,
+
+    /// MSI capability, if present
+    pub msi: Option<MsiCapability>,
+
+    /// MSI-X capability, if present
+    pub msix: Option<MsixCapability>,
+
+    /// Current interrupt mode
+    pub irq_mode: PciIrqMode,
+
+    /// Vectors allocated for MSI / MSI-X
+    pub irq_block: Option<MsiBlock>,
+}
+
+impl BusDevice {
+    fn new(info: PciDevice, ecam: usize) -> Self {
+        let msi = MsiCapability::read(ecam, &info.addr);
+        let msix = MsixCapability::read(ecam, &info.addr);
+        Self { info, ecam, msi, msix, irq_mode: PciIrqMode::Disabled, irq_block: None }
+    }
+
+    /// Virtual address of a config space register
+    fn config_vaddr(&self, offset: u16) -> usize {
+        let addr = &self.info.addr;
+        let paddr = self.ecam as u64
+            + addr.bus as u64 * PCIE_ECAM_BYTE_PER_BUS
+            + ((addr.device as u64) << 15)
+            + ((addr.function as u64) << 12)
+            + offset as u64;
+        crate::kernel::mmu::phys_to_virt(paddr)
+    }
+
+    fn check_access(offset: u16, width: usize) -> Result<()> {
+        if width != 1 && width != 2 && width != 4 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if offset as usize % width != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if offset as usize + width > PCIE_EXTENDED_CONFIG_SIZE as usize {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        Ok(())
+    }
+
+    /// Read `width` bytes of config space at `offset`
+    pub fn config_read(&self, offset: u16, width: usize) -> Result<u32> {
+        Self::check_access(offset, width)?;
+        let va = self.config_vaddr(offset);
+        let value = unsafe {
+            match width {
+                1 => core::ptr::read_volatile(va as *const u8) as u32,
+                2 => core::ptr::read_volatile(va as *const u16) as u32,
+                _ => core::ptr::read_volatile(va as *const u32),
+            }
+        };
+        Ok(value)
+    }
+
+    /// Write `width` bytes of config space at `offset`
+    pub fn config_write(&self, offset: u16, width: usize, value: u32) -> Result<()> {
+        Self::check_access(offset, width)?;
+        let va = self.config_vaddr(offset);
+        unsafe {
+            match width {
+                1 => core::ptr::write_volatile(va as *mut u8, value as u8),
+                2 => core::ptr::write_volatile(va as *mut u16, value as u16),
+                _ => core::ptr::write_volatile(va as *mut u32, value),
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&self) -> u16 {
+        let addr = &self.info.addr;
+        pci_get_command(self.ecam, addr.bus, addr.device, addr.function)
+    }
+
+    fn set_command(&self, value: u16) {
+        let addr = &self.info.addr;
+        pci_set_command(self.ecam, addr.bus, addr.device, addr.function, value);
+    }
+
+    /// Enable or disable bus mastering (DMA and MSI delivery)
+    pub fn set_bus_master(&self, enable: bool) {
+        let cmd = self.command();
+        if enable {
+            self.set_command(cmd | PCI_COMMAND_BUS_MASTER_EN);
+        } else {
+            self.set_command(cmd & !PCI_COMMAND_BUS_MASTER_EN);
+        }
+    }
+
+    /// Get a BAR
+    pub fn bar(&self, index: usize) -> Option<PciBar> {
+        self.info.bars.get(index).copied().flatten()
+    }
+
+    /// Number of interrupts the function supports in `mode`
+    pub fn query_irq_mode(&self, mode: PciIrqMode) -> Result<u32> {
+        match mode {
+            PciIrqMode::Legacy => Ok(if self.info.irq_pin != 0 { 1 } else { 0 }),
+            PciIrqMode::Msi if msi_is_supported() => {
+                self.msi.map(|cap| cap.max_vectors).ok_or(RX_ERR_NOT_SUPPORTED)
+            }
+            PciIrqMode::MsiX if msi_is_supported() => {
+                self.msix.map(|cap| cap.table_size).ok_or(RX_ERR_NOT_SUPPORTED)
+            }
+            _ => Err(RX_ERR_NOT_SUPPORTED),
+        }
+    }
+
+    /// Switch to `mode` with `count` interrupts
+    ///
+    /// A count of zero disables interrupts.
+    pub fn set_irq_mode(&mut self, mode: PciIrqMode, count: u32) -> Result<()> {
+        if count == 0 {
+            self.disable_irqs();
+            return Ok(());
+        }
+        if count > self.query_irq_mode(mode)? {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        self.disable_irqs();
+        match mode {
+            PciIrqMode::Legacy => {
+                self.set_command(self.command() & !PCI_COMMAND_INT_DISABLE);
+            }
+            PciIrqMode::Msi => {
+                let cap = self.msi.ok_or(RX_ERR_NOT_SUPPORTED)?;
+                let block = msi_alloc_block(count, false)?;
+                unsafe { msi_enable(self.ecam, &self.info.addr, &cap, &block) };
+                self.irq_block = Some(block);
+            }
+            PciIrqMode::MsiX => {
+                let cap = self.msix.ok_or(RX_ERR_NOT_SUPPORTED)?;
+                let table_bar = self.bar(cap.table_bar as usize).ok_or(RX_ERR_BAD_STATE)?;
+                let block = msi_alloc_block(count, true)?;
+                let table_paddr = table_bar.base + cap.table_offset as u64;
+                unsafe { msix_enable(self.ecam, &self.info.addr, &cap, table_paddr, &block) };
+                self.irq_block = Some(block);
+            }
+            PciIrqMode::Disabled => {}
+        }
+        self.irq_mode = mode;
+        Ok(())
+    }
+
+    fn disable_irqs(&mut self) {
+        match self.irq_mode {
+            PciIrqMode::Msi => {
+                if let Some(cap) = &self.msi {
+                    unsafe { msi_disable(self.ecam, &self.info.addr, cap) };
+                }
+            }
+            PciIrqMode::MsiX => {
+                if let Some(cap) = &self.msix {
+                    unsafe { msix_disable(self.ecam, &self.info.addr, cap) };
+                }
+            }
+            PciIrqMode::Legacy | PciIrqMode::Disabled => {}
+        }
+        if let Some(block) = self.irq_block.take() {
+            msi_free_block(&block);
+        }
+        self.set_command(self.command() | PCI_COMMAND_INT_DISABLE);
+        self.irq_mode = PciIrqMode::Disabled;
+    }
+
+    /// Platform interrupt number of interrupt `which` in the current mode
+    pub fn map_interrupt(&self, which: u32) -> Result<u32> {
+        match self.irq_mode {
+            PciIrqMode::Legacy if which == 0 => Ok(self.info.irq_line as u32),
+            PciIrqMode::Msi | PciIrqMode::MsiX => {
+                let block = self.irq_block.ok_or(RX_ERR_BAD_STATE)?;
+                if which < block.count {
+                    Ok(block.base_irq + which)
+                } else {
+                    Err(RX_ERR_INVALID_ARGS)
+                }
+            }
+            PciIrqMode::Disabled => Err(RX_ERR_BAD_STATE),
+            _ => Err(RX_ERR_INVALID_ARGS),
+        }
+    }
+}
+
+/// ============================================================================
+/// Bus State
+/// ============================================================================
+
+/// Enumerated PCI topology
+pub struct PciBus {
+    regions: Vec<EcamRegion>,
+    devices: Vec<BusDevice>,
+}
+
+impl PciBus {
+    /// Empty bus
+    pub const fn new() -> Self {
+        Self { regions: Vec::new(), devices: Vec::new() }
+    }
+
+    /// ECAM windows in use
+    pub fn regions(&self) -> &[EcamRegion] {
+        &self.regions
+    }
+
+    /// Enumerated devices
+    pub fn devices(&self) -> &[BusDevice] {
+        &self.devices
+    }
+}
+
+/// Global bus state
+static PCI_BUS: SpinMutex<PciBus> = SpinMutex::new(PciBus::new());
+
+/// Set once enumeration has run
+static PCI_READY: AtomicBool = AtomicBool::new(false);
+
+/// ============================================================================
+/// Enumeration
+/// ============================================================================
+
+/// Enumerate all PCI functions behind the platform ECAM windows
+pub fn init() {
+    let regions = platform_ecam_regions();
+    if regions.is_empty() {
+        log_info!("PCI: no ECAM windows");
+        return;
+    }
+
+    let host = crate::kernel::dev::fdt::platform_info().and_then(|info| info.pci_host);
+    let mut io = host.and_then(|h| h.io).map(|w| BarWindow::new(w, PIO_ALLOC_FLOOR));
+    let mut mmio32 = host.and_then(|h| h.mmio32).map(|w| BarWindow::new(w, 0));
+    let mut mmio64 = host.and_then(|h| h.mmio64).map(|w| BarWindow::new(w, 0));
+
+    let mut devices = Vec::new();
+    for region in &regions {
+        let ecam = bus0_base(region);
+        for bus in region.bus_start..=region.bus_end {
+            for mut info in unsafe { pci_scan_bus(ecam, bus) } {
+                info.addr.segment = region.segment;
+                if bus == region.bus_start {
+                    assign_bars(ecam, &mut info, &mut io, &mut mmio32, &mut mmio64);
+                }
+                devices.push(BusDevice::new(info, ecam));
+            }
+        }
+    }
+
+    log_info!("PCI: {} functions in {} ECAM windows", devices.len(), regions.len());
+
+    let mut bus = PCI_BUS.lock();
+    bus.regions = regions;
+    bus.devices = devices;
+    PCI_READY.store(true, Ordering::Release);
+}
+
+/// Physical ECAM address of bus 0 in a region
+fn bus0_base(region: &EcamRegion) -> usize {
+    (region.base - region.bus_start as u64 * PCIE_ECAM_BYTE_PER_BUS) as usize
+}
+
+/// Program BARs left unassigned by firmware and enable decoding
+fn assign_bars(
+    ecam: usize,
+    info: &mut PciDevice,
+    io: &mut Option<BarWindow>,
+    mmio32: &mut Option<BarWindow>,
+    mmio64: &mut Option<BarWindow>,
+) {
+    let addr = info.addr;
+    let mut decode = 0u16;
+
+    for slot in info.bars.iter_mut() {
+        let bar = match slot {
+            Some(bar) => bar,
+            None => continue,
+        };
+
+        if bar.base == 0 {
+            let window = match bar.addr_space {
+                PciAddrSpace::PIO => io.as_mut(),
+                PciAddrSpace::MMIO if bar.is_64bit && bar.is_prefetchable && mmio64.is_some() => {
+                    mmio64.as_mut()
+                }
+                PciAddrSpace::MMIO => mmio32.as_mut(),
+            };
+            let (pci_addr, cpu_addr) = match window.and_then(|w| w.alloc(bar.size)) {
+                Some(pair) => pair,
+                None => {
+                    if io.is_some() || mmio32.is_some() {
+                        log_warn!(
+                            "PCI: no room for BAR{} of {:02x}:{:02x}.{}",
+                            bar.index, addr.bus, addr.device, addr.function
+                        );
+                    }
+                    continue;
+                }
+            };
+
+            let offset = PCI_CONFIG_BASE_ADDRESSES + bar.index * 4;
+            unsafe {
+                pci_conf_write32(ecam, &PcieAddr { offset, ..addr }, pci_addr as u32);
+                if bar.is_64bit {
+                    pci_conf_write32(ecam, &PcieAddr { offset: offset + 4, ..addr }, (pci_addr >> 32) as u32);
+                }
+            }
+            bar.base = cpu_addr;
+        }
+
+        decode |= match bar.addr_space {
+            PciAddrSpace::PIO => PCI_COMMAND_IO_EN,
+            PciAddrSpace::MMIO => PCI_COMMAND_MEM_EN,
+        };
+    }
+
+    if decode != 0 {
+        let cmd = pci_get_command(ecam, addr.bus, addr.device, addr.function);
+        pci_set_command(ecam, addr.bus, addr.device, addr.function, cmd | decode);
+    }
+}
+
+/// ============================================================================
+/// Queries
+/// ============================================================================
+
+/// Check whether enumeration has run
+pub fn is_initialized() -> bool {
+    PCI_READY.load(Ordering::Acquire)
+}
+
+/// Number of enumerated functions
+pub fn device_count() -> usize {
+    PCI_BUS.lock().devices.len()
+}
+
+/// Run `f` with the device at `index`
+pub fn with_device<R>(index: usize, f: impl FnOnce(&mut BusDevice) -> R) -> Result<R> {
+    let mut bus = PCI_BUS.lock();
+    let dev = bus.devices.get_mut(index).ok_or(RX_ERR_NOT_FOUND)?;
+    Ok(f(dev))
+}
+
+/// Print the device list
+pub fn lspci() {
+    let bus = PCI_BUS.lock();
+    for dev in &bus.devices {
+        let info = &dev.info;
+        let addr = &info.addr;
+        crate::println!(
+            "{:04x}:{:02x}:{:02x}.{} {:04x}:{:04x} {} ({:02x}{:02x}{:02x}) rev {:02x}{}{}",
+            addr.segment,
+            addr.bus,
+            addr.device,
+            addr.function,
+            info.vendor_id,
+            info.device_id,
+            info.class_name(),
+            info.class_code.0,
+            info.class_code.1,
+            info.class_code.2,
+            info.revision_id,
+            if dev.msi.is_some() { " msi" } else { "" },
+            if dev.msix.is_some() { " msix" } else { "" }
+        );
+        for bar in info.bars.iter().flatten() {
+            let space = match bar.addr_space {
+                PciAddrSpace::MMIO => "mem",
+                PciAddrSpace::PIO => "io",
+            };
+            crate::println!(
+                "    BAR{}: {} {:#x} size {:#x}{}{}",
+                bar.index,
+                space,
+                bar.base,
+                bar.size,
+                if bar.is_64bit { " 64bit" } else { "" },
+                if bar.is_prefetchable { " prefetch" } else { "" }
+            );
+        }
+    }
+}
+
+/// `lspci` console command
+fn cmd_lspci(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    if !is_initialized() {
+        crate::println!("PCI not initialized");
+        return -1;
+    }
+    lspci();
+    0
+}
+
+crate::static_command!("lspci", "list PCI devices", cmd_lspci);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_window_alignment() {
+        let window = PciWindow { pci_base: 0x1000_0000, cpu_base: 0x1000_0000, size: 0x10_0000 };
+        let mut bars = BarWindow::new(window, 0);
+
+        assert_eq!(bars.alloc(0x1000), Some((0x1000_0000, 0x1000_0000)));
+        // The next 64 KiB BAR is naturally aligned
+        assert_eq!(bars.alloc(0x1_0000), Some((0x1001_0000, 0x1001_0000)));
+        assert_eq!(bars.alloc(0x10_0000), None);
+        assert_eq!(bars.alloc(3), None);
+    }
+
+    #[test]
+    fn test_bar_window_translation() {
+        let window = PciWindow { pci_base: 0, cpu_base: 0x3eff_0000, size: 0x1_0000 };
+        let mut bars = BarWindow::new(window, PIO_ALLOC_FLOOR);
+
+        assert_eq!(bars.alloc(0x20), Some((0x1000, 0x3eff_1000)));
+    }
+
+    #[test]
+    fn test_config_access_checks() {
+        assert!(BusDevice::check_access(0x40, 4).is_ok());
+        assert_eq!(BusDevice::check_access(0x41, 2), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(BusDevice::check_access(0x40, 3), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(BusDevice::check_access(0xffc, 4), Ok(()));
+        assert_eq!(BusDevice::check_access(0x1000, 1), Err(RX_ERR_OUT_OF_RANGE));
+    }
+}
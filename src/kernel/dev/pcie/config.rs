@@ -46,11 +46,12 @@ impl PcieAddr {
     }
 
     /// Check if the address is valid
+    ///
+    /// A `u8` offset always lies within the configuration space.
     pub fn is_valid(&self) -> bool {
         (self.bus as u16) < PCIE_MAX_BUSES
             && self.device < PCIE_MAX_DEVICES_PER_BUS
             && self.function < PCIE_MAX_FUNCTIONS_PER_DEVICE
-            && self.offset < PCIE_EXTENDED_CONFIG_SIZE as u8
     }
 
     /// Convert to ECAM address
@@ -166,13 +166,10 @@ pub unsafe fn pci_scan_function(
         _ => 0,
     };
 
-    for i in 0..num_bars {
+    let mut i = 0;
+    while i < num_bars {
         let bar_value = pci_get_bar(ecam_base, bus, device, function, i as u8);
 
-        if bar_value == 0 {
-            continue;
-        }
-
         // Determine BAR type
         let addr_space = if (bar_value & (PCI_BAR_IO_TYPE_MASK as u64)) == (PCI_BAR_IO_TYPE_PIO as u64) {
             PciAddrSpace::PIO
@@ -192,22 +189,23 @@ pub unsafe fn pci_scan_function(
             PciAddrSpace::MMIO => bar_value & (PCI_BAR_MMIO_ADDR_MASK as u64),
         };
 
-        // Determine size by writing all 1s and reading back
-        let size = pci_get_bar_size(ecam_base, bus, device, function, i as u8);
-
-        pci_dev.bars[i] = Some(PciBar::new(
-            i as u8,
-            base,
-            size,
-            addr_space,
-            is_64bit,
-            is_prefetchable,
-        ));
-
-        // Skip next BAR if this is 64-bit
-        if is_64bit {
-            pci_dev.bars[i + 1] = None;
+        // Determine size by writing all 1s and reading back. Unassigned
+        // BARs read as zero but still report a size.
+        let size = pci_get_bar_size(ecam_base, bus, device, function, i as u8, is_64bit);
+
+        if size != 0 {
+            pci_dev.bars[i] = Some(PciBar::new(
+                i as u8,
+                base,
+                size,
+                addr_space,
+                is_64bit,
+                is_prefetchable,
+            ));
         }
+
+        // The upper half of a 64-bit BAR is not a BAR of its own
+        i += if is_64bit { 2 } else { 1 };
     }
 
     Some(pci_dev)
@@ -215,6 +213,9 @@ pub unsafe fn pci_scan_function(
 
 /// Get the size of a BAR by probing
 ///
+/// Decoding is disabled while the BAR holds the all-ones probe value so
+/// the device never responds at a bogus address.
+///
 /// # Safety
 ///
 /// ecam_base must be a valid mapped ECAM region
@@ -224,36 +225,50 @@ unsafe fn pci_get_bar_size(
     device: u8,
     function: u8,
     bar_index: u8,
+    is_64bit: bool,
 ) -> u64 {
     let offset = PCI_CONFIG_BASE_ADDRESSES + (bar_index * 4);
     let addr = PcieAddr::new(0, bus, device, function, offset);
-
-    // Save original value
+    let addr_high = PcieAddr::new(0, bus, device, function, offset + 4);
+
+    let command = pci_get_command(ecam_base, bus, device, function);
+    pci_set_command(
+        ecam_base,
+        bus,
+        device,
+        function,
+        command & !(PCI_COMMAND_IO_EN | PCI_COMMAND_MEM_EN),
+    );
+
+    // Save original value, write all 1s and read back the size mask
     let original = pci_conf_read32(ecam_base, &addr);
-
-    // Write all 1s
     pci_conf_write32(ecam_base, &addr, 0xFFFF_FFFF);
-
-    // Read back to get size info
     let size_mask = pci_conf_read32(ecam_base, &addr);
-
-    // Restore original value
     pci_conf_write32(ecam_base, &addr, original);
 
-    // Extract size from mask
-    let size = if (size_mask & PCI_BAR_IO_TYPE_MASK) == PCI_BAR_IO_TYPE_PIO {
-        // PIO
-        (!(size_mask & PCI_BAR_PIO_ADDR_MASK)) & 0xFFFF_FFFF
+    let mut size_mask_high = 0xFFFF_FFFFu32;
+    if is_64bit {
+        let original_high = pci_conf_read32(ecam_base, &addr_high);
+        pci_conf_write32(ecam_base, &addr_high, 0xFFFF_FFFF);
+        size_mask_high = pci_conf_read32(ecam_base, &addr_high);
+        pci_conf_write32(ecam_base, &addr_high, original_high);
+    }
+
+    pci_set_command(ecam_base, bus, device, function, command);
+
+    // Extract the writable address bits
+    let low = if (size_mask & PCI_BAR_IO_TYPE_MASK) == PCI_BAR_IO_TYPE_PIO {
+        size_mask & PCI_BAR_PIO_ADDR_MASK
     } else {
-        // MMIO
-        (!(size_mask & PCI_BAR_MMIO_ADDR_MASK)) & 0xFFFF_FFFF
+        size_mask & PCI_BAR_MMIO_ADDR_MASK
     };
+    let mask = ((size_mask_high as u64) << 32) | low as u64;
 
-    // Get lowest set bit (actual size)
-    if size == 0 {
+    // Lowest writable bit is the size
+    if mask == 0 || (low == 0 && !is_64bit) {
         0
     } else {
-        1u64 << (size.trailing_zeros() as u64)
+        1u64 << mask.trailing_zeros()
     }
 }
 
@@ -32,10 +32,12 @@
 //! ```
 
 
+pub mod bus;
 pub mod constants;
 pub mod config;
 pub mod device;
 pub mod ecam;
+pub mod msi;
 
 // Re-exports
 pub use constants::*;
@@ -43,6 +45,41 @@ pub use config::*;
 pub use device::*;
 pub use ecam::*;
 
+use alloc::vec::Vec;
+
+/// Discover ECAM windows from firmware tables
+///
+/// On ACPI platforms the windows come from the MCFG. MCFG bases describe
+/// bus 0 of the segment, while `EcamRegion` is based at its first bus, so
+/// the base is adjusted by `bus_start`. On device tree platforms the
+/// window comes from the `pci-host-ecam-generic` node, whose `reg` is
+/// already based at the first bus.
+pub fn platform_ecam_regions() -> Vec<EcamRegion> {
+    let mut regions = Vec::new();
+
+    for entry in crate::kernel::dev::acpi::mcfg().entries() {
+        let base = entry.base + entry.bus_start as u64 * PCIE_ECAM_BYTE_PER_BUS;
+        regions.push(EcamRegion::new(
+            base,
+            entry.segment,
+            entry.bus_start,
+            entry.bus_end,
+        ));
+    }
+
+    if regions.is_empty() {
+        let host = crate::kernel::dev::fdt::platform_info().and_then(|info| info.pci_host);
+        if let Some(host) = host {
+            // Never decode more buses than the window covers
+            let buses = (host.ecam_size / PCIE_ECAM_BYTE_PER_BUS).max(1);
+            let bus_end = (host.bus_start as u64 + buses - 1).min(host.bus_end as u64) as u8;
+            regions.push(EcamRegion::new(host.ecam_base, host.segment, host.bus_start, bus_end));
+        }
+    }
+
+    regions
+}
+
 /// PCIe address space type
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
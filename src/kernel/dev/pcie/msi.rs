@@ -0,0 +1,410 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PCI MSI and MSI-X Support
+//!
+//! This module walks the capability list, allocates blocks of interrupt
+//! vectors and programs the MSI / MSI-X capabilities of a function.
+//!
+//! # Design
+//!
+//! On x86 an MSI is a write of `data` to the local APIC window at
+//! 0xFEE0_0000, so a block of vectors is all that needs allocating.
+//! MSI requires a naturally aligned power-of-two block because the
+//! device ORs the vector index into the low data bits; MSI-X entries are
+//! programmed individually. Other architectures need an MSI controller
+//! (GICv2m/ITS, IMSIC) which is not supported yet.
+
+use crate::kernel::dev::pcie::config::*;
+use crate::kernel::dev::pcie::constants::*;
+use crate::kernel::dev::pcie::device::pci_cap_id::{PCI_CAP_ID_MSI, PCI_CAP_ID_MSIX};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::*;
+
+/// First vector handed out for MSI
+pub const MSI_VECTOR_BASE: u32 = 0x60;
+
+/// Number of vectors available for MSI
+pub const MSI_VECTOR_COUNT: u32 = 128;
+
+/// x86 MSI address window (destination APIC ID in bits 12..19)
+const X86_MSI_ADDRESS_BASE: u64 = 0xFEE0_0000;
+
+/// Status register bit: capability list present
+const PCI_STATUS_CAP_LIST: u16 = 1 << 4;
+
+/// MSI message control bits
+const MSI_CTRL_ENABLE: u16 = 1 << 0;
+const MSI_CTRL_MMC_SHIFT: u16 = 1;
+const MSI_CTRL_MME_SHIFT: u16 = 4;
+const MSI_CTRL_MME_MASK: u16 = 0x7 << MSI_CTRL_MME_SHIFT;
+const MSI_CTRL_64BIT: u16 = 1 << 7;
+const MSI_CTRL_PVM: u16 = 1 << 8;
+
+/// MSI-X message control bits
+const MSIX_CTRL_TABLE_SIZE_MASK: u16 = 0x7FF;
+const MSIX_CTRL_FUNCTION_MASK: u16 = 1 << 14;
+const MSIX_CTRL_ENABLE: u16 = 1 << 15;
+
+/// MSI-X table entry layout
+const MSIX_ENTRY_SIZE: usize = 16;
+const MSIX_VECTOR_CTRL_MASKED: u32 = 1;
+
+/// Legacy / MSI / MSI-X interrupt mode of a function
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PciIrqMode {
+    /// No interrupts
+    Disabled = 0xFFFF_FFFF,
+
+    /// INTx pin
+    Legacy = 0,
+
+    /// Message Signaled Interrupts
+    Msi = 1,
+
+    /// Extended MSI
+    MsiX = 2,
+}
+
+impl PciIrqMode {
+    /// Convert from the syscall value
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Legacy),
+            1 => Some(Self::Msi),
+            2 => Some(Self::MsiX),
+            _ => None,
+        }
+    }
+}
+
+/// ============================================================================
+/// Capabilities
+/// ============================================================================
+
+/// Find a capability by ID, returning its config space offset
+pub fn find_capability(ecam_base: usize, addr: &PcieAddr, id: u8) -> Option<u8> {
+    let status = unsafe {
+        pci_conf_read16(ecam_base, &PcieAddr { offset: PCI_CONFIG_STATUS, ..*addr })
+    };
+    if status & PCI_STATUS_CAP_LIST == 0 {
+        return None;
+    }
+
+    let mut ptr = unsafe {
+        pci_conf_read8(ecam_base, &PcieAddr { offset: PCI_CONFIG_CAPABILITIES, ..*addr })
+    } & !0x3;
+
+    // Bound the walk in case of a looping list
+    for _ in 0..PCIE_MAX_CAPABILITIES {
+        if ptr < PCIE_CAP_PTR_MIN_VALID {
+            return None;
+        }
+        let cap_id = unsafe { pci_conf_read8(ecam_base, &PcieAddr { offset: ptr, ..*addr }) };
+        if cap_id == id {
+            return Some(ptr);
+        }
+        ptr = unsafe { pci_conf_read8(ecam_base, &PcieAddr { offset: ptr + 1, ..*addr }) } & !0x3;
+    }
+    None
+}
+
+/// MSI capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiCapability {
+    /// Config space offset
+    pub offset: u8,
+
+    /// Supports a 64-bit message address
+    pub is_64bit: bool,
+
+    /// Supports per-vector masking
+    pub per_vector_mask: bool,
+
+    /// Maximum vectors the function requests
+    pub max_vectors: u32,
+}
+
+impl MsiCapability {
+    /// Read the MSI capability of a function
+    pub fn read(ecam_base: usize, addr: &PcieAddr) -> Option<Self> {
+        let offset = find_capability(ecam_base, addr, PCI_CAP_ID_MSI)?;
+        let ctrl = unsafe { pci_conf_read16(ecam_base, &PcieAddr { offset: offset + 2, ..*addr }) };
+        Some(Self {
+            offset,
+            is_64bit: ctrl & MSI_CTRL_64BIT != 0,
+            per_vector_mask: ctrl & MSI_CTRL_PVM != 0,
+            max_vectors: 1 << ((ctrl >> MSI_CTRL_MMC_SHIFT) & 0x7).min(5),
+        })
+    }
+}
+
+/// MSI-X capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsixCapability {
+    /// Config space offset
+    pub offset: u8,
+
+    /// Number of table entries
+    pub table_size: u32,
+
+    /// BAR holding the vector table, and offset within it
+    pub table_bar: u8,
+    pub table_offset: u32,
+
+    /// BAR holding the pending bit array, and offset within it
+    pub pba_bar: u8,
+    pub pba_offset: u32,
+}
+
+impl MsixCapability {
+    /// Read the MSI-X capability of a function
+    pub fn read(ecam_base: usize, addr: &PcieAddr) -> Option<Self> {
+        let offset = find_capability(ecam_base, addr, PCI_CAP_ID_MSIX)?;
+        let (ctrl, table, pba) = unsafe {
+            (
+                pci_conf_read16(ecam_base, &PcieAddr { offset: offset + 2, ..*addr }),
+                pci_conf_read32(ecam_base, &PcieAddr { offset: offset + 4, ..*addr }),
+                pci_conf_read32(ecam_base, &PcieAddr { offset: offset + 8, ..*addr }),
+            )
+        };
+        Some(Self {
+            offset,
+            table_size: (ctrl & MSIX_CTRL_TABLE_SIZE_MASK) as u32 + 1,
+            table_bar: (table & 0x7) as u8,
+            table_offset: table & !0x7,
+            pba_bar: (pba & 0x7) as u8,
+            pba_offset: pba & !0x7,
+        })
+    }
+}
+
+/// ============================================================================
+/// Vector Allocation
+/// ============================================================================
+
+/// A block of MSI vectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiBlock {
+    /// First platform interrupt number
+    pub base_irq: u32,
+
+    /// Number of vectors
+    pub count: u32,
+
+    /// Message address
+    pub target_addr: u64,
+
+    /// Message data for the first vector
+    pub target_data: u32,
+}
+
+/// Bitmap allocator over the MSI vector range
+#[derive(Debug, Clone, Copy)]
+pub struct VectorAllocator {
+    used: u128,
+}
+
+impl VectorAllocator {
+    /// All vectors free
+    pub const fn new() -> Self {
+        Self { used: 0 }
+    }
+
+    /// Allocate `count` vectors aligned to `align`, returning the index of
+    /// the first
+    pub fn alloc(&mut self, count: u32, align: u32) -> Option<u32> {
+        if count == 0 || count > MSI_VECTOR_COUNT || !align.is_power_of_two() {
+            return None;
+        }
+        let mask = if count == 128 { u128::MAX } else { (1u128 << count) - 1 };
+
+        let mut start = 0;
+        while start + count <= MSI_VECTOR_COUNT {
+            if self.used & (mask << start) == 0 {
+                self.used |= mask << start;
+                return Some(start);
+            }
+            start += align;
+        }
+        None
+    }
+
+    /// Free `count` vectors starting at index `start`
+    pub fn free(&mut self, start: u32, count: u32) {
+        if count == 0 || start + count > MSI_VECTOR_COUNT {
+            return;
+        }
+        let mask = if count == 128 { u128::MAX } else { (1u128 << count) - 1 };
+        self.used &= !(mask << start);
+    }
+}
+
+/// Global MSI vector allocator
+static MSI_VECTORS: SpinMutex<VectorAllocator> = SpinMutex::new(VectorAllocator::new());
+
+/// Check whether the platform can deliver MSIs
+pub fn msi_is_supported() -> bool {
+    cfg!(target_arch = "x86_64")
+}
+
+/// Allocate a block of `count` vectors
+///
+/// MSI blocks are rounded up to a power of two and naturally aligned.
+pub fn msi_alloc_block(count: u32, is_msix: bool) -> Result<MsiBlock> {
+    if !msi_is_supported() {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    if count == 0 || count > MSI_VECTOR_COUNT {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    let (count, align) = if is_msix {
+        (count, 1)
+    } else {
+        let rounded = count.next_power_of_two();
+        (rounded, rounded)
+    };
+
+    let start = MSI_VECTORS.lock().alloc(count, align).ok_or(RX_ERR_NO_RESOURCES)?;
+    let vector = MSI_VECTOR_BASE + start;
+
+    // Fixed delivery to the boot CPU's local APIC
+    Ok(MsiBlock {
+        base_irq: vector,
+        count,
+        target_addr: X86_MSI_ADDRESS_BASE,
+        target_data: vector,
+    })
+}
+
+/// Return a block to the allocator
+pub fn msi_free_block(block: &MsiBlock) {
+    if block.base_irq >= MSI_VECTOR_BASE {
+        MSI_VECTORS.lock().free(block.base_irq - MSI_VECTOR_BASE, block.count);
+    }
+}
+
+/// ============================================================================
+/// Programming
+/// ============================================================================
+
+/// Program and enable MSI with `block`
+///
+/// # Safety
+///
+/// `ecam_base` must address the function's configuration space.
+pub unsafe fn msi_enable(ecam_base: usize, addr: &PcieAddr, cap: &MsiCapability, block: &MsiBlock) {
+    let at = |off: u8| PcieAddr { offset: cap.offset + off, ..*addr };
+
+    pci_conf_write32(ecam_base, &at(4), block.target_addr as u32);
+    let data_off = if cap.is_64bit {
+        pci_conf_write32(ecam_base, &at(8), (block.target_addr >> 32) as u32);
+        12
+    } else {
+        8
+    };
+    pci_conf_write16(ecam_base, &at(data_off), block.target_data as u16);
+
+    let mme = block.count.trailing_zeros() as u16;
+    let mut ctrl = pci_conf_read16(ecam_base, &at(2));
+    ctrl = (ctrl & !MSI_CTRL_MME_MASK) | (mme << MSI_CTRL_MME_SHIFT) | MSI_CTRL_ENABLE;
+    pci_conf_write16(ecam_base, &at(2), ctrl);
+}
+
+/// Disable MSI
+///
+/// # Safety
+///
+/// `ecam_base` must address the function's configuration space.
+pub unsafe fn msi_disable(ecam_base: usize, addr: &PcieAddr, cap: &MsiCapability) {
+    let ctrl_addr = PcieAddr { offset: cap.offset + 2, ..*addr };
+    let ctrl = pci_conf_read16(ecam_base, &ctrl_addr);
+    pci_conf_write16(ecam_base, &ctrl_addr, ctrl & !(MSI_CTRL_ENABLE | MSI_CTRL_MME_MASK));
+}
+
+/// Program and enable MSI-X with `block`
+///
+/// Entries beyond `block.count` are left masked.
+///
+/// # Safety
+///
+/// `ecam_base` must address the function's configuration space and
+/// `table_paddr` must be the physical address of its vector table.
+pub unsafe fn msix_enable(
+    ecam_base: usize,
+    addr: &PcieAddr,
+    cap: &MsixCapability,
+    table_paddr: PAddr,
+    block: &MsiBlock,
+) {
+    let ctrl_addr = PcieAddr { offset: cap.offset + 2, ..*addr };
+
+    // Mask the whole function while the table is rewritten
+    let ctrl = pci_conf_read16(ecam_base, &ctrl_addr);
+    pci_conf_write16(ecam_base, &ctrl_addr, ctrl | MSIX_CTRL_ENABLE | MSIX_CTRL_FUNCTION_MASK);
+
+    let table = crate::kernel::mmu::phys_to_virt(table_paddr) as *mut u32;
+    for i in 0..cap.table_size as usize {
+        let entry = table.add(i * MSIX_ENTRY_SIZE / 4);
+        if (i as u32) < block.count {
+            core::ptr::write_volatile(entry, block.target_addr as u32);
+            core::ptr::write_volatile(entry.add(1), (block.target_addr >> 32) as u32);
+            core::ptr::write_volatile(entry.add(2), block.target_data + i as u32);
+            core::ptr::write_volatile(entry.add(3), 0);
+        } else {
+            core::ptr::write_volatile(entry.add(3), MSIX_VECTOR_CTRL_MASKED);
+        }
+    }
+
+    pci_conf_write16(ecam_base, &ctrl_addr, (ctrl | MSIX_CTRL_ENABLE) & !MSIX_CTRL_FUNCTION_MASK);
+}
+
+/// Disable MSI-X
+///
+/// # Safety
+///
+/// `ecam_base` must address the function's configuration space.
+pub unsafe fn msix_disable(ecam_base: usize, addr: &PcieAddr, cap: &MsixCapability) {
+    let ctrl_addr = PcieAddr { offset: cap.offset + 2, ..*addr };
+    let ctrl = pci_conf_read16(ecam_base, &ctrl_addr);
+    pci_conf_write16(ecam_base, &ctrl_addr, ctrl & !MSIX_CTRL_ENABLE);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_allocator_alignment() {
+        let mut alloc = VectorAllocator::new();
+        assert_eq!(alloc.alloc(1, 1), Some(0));
+        // A block of four must start on a multiple of four
+        assert_eq!(alloc.alloc(4, 4), Some(4));
+        assert_eq!(alloc.alloc(2, 1), Some(1));
+
+        alloc.free(4, 4);
+        assert_eq!(alloc.alloc(4, 4), Some(4));
+    }
+
+    #[test]
+    fn test_vector_allocator_exhaustion() {
+        let mut alloc = VectorAllocator::new();
+        assert_eq!(alloc.alloc(MSI_VECTOR_COUNT, 1), Some(0));
+        assert_eq!(alloc.alloc(1, 1), None);
+        alloc.free(0, MSI_VECTOR_COUNT);
+        assert_eq!(alloc.alloc(0, 1), None);
+        assert_eq!(alloc.alloc(1, 3), None);
+    }
+
+    #[test]
+    fn test_irq_mode_from_raw() {
+        assert_eq!(PciIrqMode::from_raw(1), Some(PciIrqMode::Msi));
+        assert_eq!(PciIrqMode::from_raw(7), None);
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TPM Command Response Buffer (CRB) Interface
+//!
+//! The command is copied into a memory buffer whose address the control
+//! area gives, then started with a register write; the response appears
+//! in a second buffer. The ACPI TPM2 table points at the control area,
+//! which sits at offset 0x40 of the locality 0 register page.
+//!
+//! Only the plain CRB start method is supported; platforms that start
+//! commands through an ACPI method need an AML interpreter.
+
+use super::{poll, response_size, TpmInterface, COMMAND_TIMEOUT_NS, HEADER_SIZE, STATE_TIMEOUT_NS};
+use crate::kernel::mmu;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+// Import logging macros
+use crate::log_warn;
+
+/// Offset of the control area in the locality page
+const CONTROL_AREA_OFFSET: u64 = 0x40;
+
+/// Locality registers, relative to the locality page
+const REG_LOC_CTRL: usize = 0x08;
+const REG_LOC_STS: usize = 0x0C;
+
+/// Control area registers
+const REG_CTRL_REQ: usize = 0x00;
+const REG_CTRL_STS: usize = 0x04;
+const REG_CTRL_START: usize = 0x0C;
+const REG_CMD_SIZE: usize = 0x18;
+const REG_CMD_LADDR: usize = 0x1C;
+const REG_CMD_HADDR: usize = 0x20;
+const REG_RSP_SIZE: usize = 0x24;
+const REG_RSP_ADDR: usize = 0x28;
+
+/// `LOC_CTRL` / `LOC_STS` bits
+const LOC_CTRL_REQUEST_ACCESS: u32 = 1 << 0;
+const LOC_STS_GRANTED: u32 = 1 << 0;
+
+/// `CTRL_REQ` bits
+const CTRL_REQ_CMD_READY: u32 = 1 << 0;
+const CTRL_REQ_GO_IDLE: u32 = 1 << 1;
+
+/// `CTRL_STS` bits
+const CTRL_STS_ERROR: u32 = 1 << 0;
+
+/// A CRB interface TPM
+pub struct CrbTpm {
+    /// Virtual address of the locality 0 registers
+    locality: usize,
+
+    /// Virtual address of the control area
+    control: usize,
+
+    /// Command buffer and its size
+    cmd_buf: usize,
+    cmd_size: usize,
+
+    /// Response buffer and its size
+    rsp_buf: usize,
+    rsp_size: usize,
+}
+
+impl CrbTpm {
+    fn read(&self, base: usize, reg: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((base + reg) as *const u32) }
+    }
+
+    fn write(&self, base: usize, reg: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((base + reg) as *mut u32, value) }
+    }
+
+    /// Move the TPM out of idle
+    fn ready(&self) -> Result {
+        self.write(self.control, REG_CTRL_REQ, CTRL_REQ_CMD_READY);
+        poll(STATE_TIMEOUT_NS, || self.read(self.control, REG_CTRL_REQ) & CTRL_REQ_CMD_READY == 0)
+    }
+
+    fn run(&self, cmd: &[u8], rsp: &mut [u8]) -> Result<usize> {
+        if cmd.len() > self.cmd_size {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        self.ready()?;
+
+        for (i, &byte) in cmd.iter().enumerate() {
+            unsafe { core::ptr::write_volatile((self.cmd_buf + i) as *mut u8, byte) };
+        }
+
+        self.write(self.control, REG_CTRL_START, 1);
+        poll(COMMAND_TIMEOUT_NS, || self.read(self.control, REG_CTRL_START) == 0)?;
+        if self.read(self.control, REG_CTRL_STS) & CTRL_STS_ERROR != 0 {
+            return Err(RX_ERR_IO);
+        }
+
+        let read_rsp = |i: usize| unsafe { core::ptr::read_volatile((self.rsp_buf + i) as *const u8) };
+        let mut header = [0u8; HEADER_SIZE];
+        for (i, byte) in header.iter_mut().enumerate() {
+            *byte = read_rsp(i);
+        }
+        let size = response_size(&header)?;
+        if size > self.rsp_size {
+            return Err(RX_ERR_IO);
+        }
+        if size > rsp.len() {
+            return Err(RX_ERR_BUFFER_TOO_SMALL);
+        }
+        for (i, byte) in rsp[..size].iter_mut().enumerate() {
+            *byte = read_rsp(i);
+        }
+        Ok(size)
+    }
+}
+
+impl TpmInterface for CrbTpm {
+    fn name(&self) -> &'static str {
+        "crb"
+    }
+
+    fn transmit(&self, cmd: &[u8], rsp: &mut [u8]) -> Result<usize> {
+        let result = self.run(cmd, rsp);
+        self.write(self.control, REG_CTRL_REQ, CTRL_REQ_GO_IDLE);
+        result
+    }
+}
+
+/// Set up the TPM whose control area is at `control_area` and claim
+/// locality 0
+pub fn probe(control_area: u64) -> Option<CrbTpm> {
+    if control_area < CONTROL_AREA_OFFSET {
+        return None;
+    }
+    let locality = mmu::phys_to_virt(control_area - CONTROL_AREA_OFFSET);
+    let control = mmu::phys_to_virt(control_area);
+
+    let read = |base: usize, reg: usize| unsafe { core::ptr::read_volatile((base + reg) as *const u32) };
+    let cmd_addr = read(control, REG_CMD_LADDR) as u64 | (read(control, REG_CMD_HADDR) as u64) << 32;
+    let rsp_addr = unsafe { core::ptr::read_volatile((control + REG_RSP_ADDR) as *const u64) };
+    let cmd_size = read(control, REG_CMD_SIZE) as usize;
+    let rsp_size = read(control, REG_RSP_SIZE) as usize;
+    if cmd_addr == 0 || rsp_addr == 0 || cmd_size < HEADER_SIZE || rsp_size < HEADER_SIZE {
+        log_warn!("TPM: CRB control area at {:#x} has no buffers", control_area);
+        return None;
+    }
+
+    let tpm = CrbTpm {
+        locality,
+        control,
+        cmd_buf: mmu::phys_to_virt(cmd_addr),
+        cmd_size,
+        rsp_buf: mmu::phys_to_virt(rsp_addr),
+        rsp_size,
+    };
+
+    tpm.write(tpm.locality, REG_LOC_CTRL, LOC_CTRL_REQUEST_ACCESS);
+    poll(STATE_TIMEOUT_NS, || tpm.read(tpm.locality, REG_LOC_STS) & LOC_STS_GRANTED != 0).ok()?;
+
+    Some(tpm)
+}
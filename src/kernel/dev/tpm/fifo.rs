@@ -0,0 +1,164 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TPM FIFO (TIS) Interface
+//!
+//! The PC client register interface at 0xFED40000, one 4 KiB page per
+//! locality. Commands are written and responses read a byte at a time
+//! through the data FIFO, in bursts no longer than the TPM advertises in
+//! the status register.
+
+use super::{poll, response_size, TpmInterface, COMMAND_TIMEOUT_NS, HEADER_SIZE, STATE_TIMEOUT_NS};
+use crate::kernel::mmu;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+/// Locality 0 register page
+const TIS_BASE: u64 = 0xFED4_0000;
+
+/// Registers
+const REG_ACCESS: usize = 0x000;
+const REG_STS: usize = 0x018;
+const REG_DATA_FIFO: usize = 0x024;
+const REG_DID_VID: usize = 0xF00;
+
+/// `ACCESS` bits
+const ACCESS_VALID: u8 = 1 << 7;
+const ACCESS_ACTIVE_LOCALITY: u8 = 1 << 5;
+const ACCESS_REQUEST_USE: u8 = 1 << 1;
+
+/// `STS` bits
+const STS_VALID: u32 = 1 << 7;
+const STS_COMMAND_READY: u32 = 1 << 6;
+const STS_GO: u32 = 1 << 5;
+const STS_DATA_AVAIL: u32 = 1 << 4;
+const STS_EXPECT: u32 = 1 << 3;
+
+/// A FIFO interface TPM
+pub struct FifoTpm {
+    /// Virtual address of the locality 0 registers
+    regs: usize,
+}
+
+impl FifoTpm {
+    fn read8(&self, reg: usize) -> u8 {
+        unsafe { core::ptr::read_volatile((self.regs + reg) as *const u8) }
+    }
+
+    fn write8(&self, reg: usize, value: u8) {
+        unsafe { core::ptr::write_volatile((self.regs + reg) as *mut u8, value) }
+    }
+
+    fn status(&self) -> u32 {
+        unsafe { core::ptr::read_volatile((self.regs + REG_STS) as *const u32) }
+    }
+
+    fn set_status(&self, value: u32) {
+        unsafe { core::ptr::write_volatile((self.regs + REG_STS) as *mut u32, value) }
+    }
+
+    /// Bytes the FIFO takes or holds before the status must be re-read
+    fn burst_count(&self) -> Result<usize> {
+        let mut burst = 0;
+        poll(STATE_TIMEOUT_NS, || {
+            burst = ((self.status() >> 8) & 0xFFFF) as usize;
+            burst != 0
+        })?;
+        Ok(burst)
+    }
+
+    fn wait_status(&self, mask: u32, timeout: u64) -> Result {
+        poll(timeout, || self.status() & mask == mask)
+    }
+
+    fn read_fifo(&self, buf: &mut [u8]) -> Result {
+        let mut done = 0;
+        while done < buf.len() {
+            self.wait_status(STS_VALID | STS_DATA_AVAIL, STATE_TIMEOUT_NS)?;
+            let burst = self.burst_count()?.min(buf.len() - done);
+            for byte in &mut buf[done..done + burst] {
+                *byte = self.read8(REG_DATA_FIFO);
+            }
+            done += burst;
+        }
+        Ok(())
+    }
+
+    fn send(&self, cmd: &[u8]) -> Result {
+        self.set_status(STS_COMMAND_READY);
+        self.wait_status(STS_COMMAND_READY, STATE_TIMEOUT_NS)?;
+
+        let mut done = 0;
+        while done < cmd.len() {
+            let burst = self.burst_count()?.min(cmd.len() - done);
+            for &byte in &cmd[done..done + burst] {
+                self.write8(REG_DATA_FIFO, byte);
+            }
+            done += burst;
+        }
+
+        // The TPM must not expect more once the whole command is in
+        self.wait_status(STS_VALID, STATE_TIMEOUT_NS)?;
+        if self.status() & STS_EXPECT != 0 {
+            return Err(RX_ERR_IO);
+        }
+
+        self.set_status(STS_GO);
+        Ok(())
+    }
+
+    fn receive(&self, rsp: &mut [u8]) -> Result<usize> {
+        self.wait_status(STS_VALID | STS_DATA_AVAIL, COMMAND_TIMEOUT_NS)?;
+
+        if rsp.len() < HEADER_SIZE {
+            return Err(RX_ERR_BUFFER_TOO_SMALL);
+        }
+        self.read_fifo(&mut rsp[..HEADER_SIZE])?;
+        let size = response_size(rsp)?;
+        if size > rsp.len() {
+            return Err(RX_ERR_BUFFER_TOO_SMALL);
+        }
+        self.read_fifo(&mut rsp[HEADER_SIZE..size])?;
+        Ok(size)
+    }
+}
+
+impl TpmInterface for FifoTpm {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn transmit(&self, cmd: &[u8], rsp: &mut [u8]) -> Result<usize> {
+        let result = self.send(cmd).and_then(|()| self.receive(rsp));
+
+        // Back to idle, dropping any unread response
+        self.set_status(STS_COMMAND_READY);
+        result
+    }
+}
+
+/// Find a FIFO TPM and claim locality 0
+pub fn probe() -> Option<FifoTpm> {
+    let tpm = FifoTpm { regs: mmu::phys_to_virt(TIS_BASE) };
+
+    let access = tpm.read8(REG_ACCESS);
+    if access == 0xFF || access & ACCESS_VALID == 0 {
+        return None;
+    }
+    let did_vid = unsafe { core::ptr::read_volatile((tpm.regs + REG_DID_VID) as *const u32) };
+    if did_vid == 0 || did_vid == u32::MAX {
+        return None;
+    }
+
+    tpm.write8(REG_ACCESS, ACCESS_REQUEST_USE);
+    poll(STATE_TIMEOUT_NS, || {
+        tpm.read8(REG_ACCESS) & (ACCESS_VALID | ACCESS_ACTIVE_LOCALITY)
+            == ACCESS_VALID | ACCESS_ACTIVE_LOCALITY
+    })
+    .ok()?;
+
+    Some(tpm)
+}
@@ -0,0 +1,311 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TPM 2.0 Driver
+//!
+//! A minimal driver that passes marshalled TPM 2.0 commands to the chip
+//! and returns the response. It does not start the TPM or manage
+//! sessions: the firmware has already run TPM2_Startup, and callers
+//! build whatever command they need. [`pcr_read`] covers the one command
+//! the kernel issues itself.
+//!
+//! # Interfaces
+//!
+//! - [`fifo`] - TIS/FIFO register interface at the fixed PC address
+//! - [`crb`] - Command Response Buffer, with the control area from the
+//!   ACPI TPM2 table
+//!
+//! The ACPI TPM2 table picks the interface; without it the driver probes
+//! for a FIFO TPM. Only locality 0 is used.
+//!
+//! # Measured Boot
+//!
+//! The UEFI loader measures the kernel and initrd into PCR 9 and the
+//! command line into PCR 8, and passes the firmware event log through the
+//! handoff. [`event_log`] returns it so an attestation agent can replay
+//! the log against a quote of those PCRs.
+
+pub mod crb;
+pub mod fifo;
+
+use crate::kernel::dev::acpi::{self, tpm2};
+use crate::kernel::handoff;
+use crate::kernel::mmu;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::timer;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use alloc::boxed::Box;
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Command and response header: tag, size, code
+pub const HEADER_SIZE: usize = 10;
+
+/// Largest command or response handled
+pub const MAX_BUFFER: usize = 4096;
+
+/// TPM_ST_NO_SESSIONS
+const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+
+/// TPM_CC_PCR_Read
+const TPM_CC_PCR_READ: u32 = 0x0000_017E;
+
+/// TPM_RC_SUCCESS
+const TPM_RC_SUCCESS: u32 = 0;
+
+/// Algorithm IDs
+pub const TPM_ALG_SHA1: u16 = 0x0004;
+pub const TPM_ALG_SHA256: u16 = 0x000B;
+pub const TPM_ALG_SHA384: u16 = 0x000C;
+pub const TPM_ALG_SHA512: u16 = 0x000D;
+
+/// PCRs in a PC client TPM
+pub const PCR_COUNT: u32 = 24;
+
+/// Longest a command may run, in nanoseconds
+///
+/// Key generation can take far longer; callers of those commands retry.
+const COMMAND_TIMEOUT_NS: u64 = 2_000_000_000;
+
+/// Longest a register state change may take, in nanoseconds
+const STATE_TIMEOUT_NS: u64 = 750_000_000;
+
+/// A TPM register interface
+pub trait TpmInterface: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Send a marshalled command and read the response into `rsp`
+    ///
+    /// Returns the response length.
+    fn transmit(&self, cmd: &[u8], rsp: &mut [u8]) -> Result<usize>;
+}
+
+/// The TPM found at boot
+static TPM: SpinMutex<Option<Box<dyn TpmInterface>>> = SpinMutex::new(None);
+
+/// Spin until `done` holds, or fail after `timeout` nanoseconds
+fn poll(timeout: u64, mut done: impl FnMut() -> bool) -> Result {
+    let deadline = timer::current_time() + timeout;
+    loop {
+        if done() {
+            return Ok(());
+        }
+        if timer::current_time() > deadline {
+            return Err(RX_ERR_TIMED_OUT);
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Response length from a response header
+fn response_size(header: &[u8]) -> Result<usize> {
+    if header.len() < HEADER_SIZE {
+        return Err(RX_ERR_IO);
+    }
+    let size = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+    if size < HEADER_SIZE {
+        return Err(RX_ERR_IO);
+    }
+    Ok(size)
+}
+
+/// Marshal TPM2_PCR_Read for one PCR in one bank
+fn pcr_read_command(index: u32, alg: u16) -> [u8; 20] {
+    let mut cmd = [0u8; 20];
+    cmd[0..2].copy_from_slice(&TPM_ST_NO_SESSIONS.to_be_bytes());
+    let len = cmd.len() as u32;
+    cmd[2..6].copy_from_slice(&len.to_be_bytes());
+    cmd[6..10].copy_from_slice(&TPM_CC_PCR_READ.to_be_bytes());
+
+    // TPML_PCR_SELECTION with one TPMS_PCR_SELECTION of 3 bitmap bytes
+    cmd[10..14].copy_from_slice(&1u32.to_be_bytes());
+    cmd[14..16].copy_from_slice(&alg.to_be_bytes());
+    cmd[16] = 3;
+    cmd[17 + (index / 8) as usize] = 1 << (index % 8);
+    cmd
+}
+
+/// Copy the digest out of a TPM2_PCR_Read response
+///
+/// Returns the digest length, or NOT_FOUND if the bank is not active.
+fn parse_pcr_read(rsp: &[u8], out: &mut [u8]) -> Result<usize> {
+    let be16 = |off: usize| rsp.get(off..off + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let be32 = |off: usize| rsp.get(off..off + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+
+    if be32(6).ok_or(RX_ERR_IO)? != TPM_RC_SUCCESS {
+        return Err(RX_ERR_IO);
+    }
+
+    // Skip pcrUpdateCounter and the echoed selection
+    let mut off = HEADER_SIZE + 4;
+    let selections = be32(off).ok_or(RX_ERR_IO)?;
+    off += 4;
+    for _ in 0..selections {
+        let size_of_select = *rsp.get(off + 2).ok_or(RX_ERR_IO)? as usize;
+        off += 3 + size_of_select;
+    }
+
+    let digests = be32(off).ok_or(RX_ERR_IO)?;
+    if digests == 0 {
+        return Err(RX_ERR_NOT_FOUND);
+    }
+    let size = be16(off + 4).ok_or(RX_ERR_IO)? as usize;
+    let digest = rsp.get(off + 6..off + 6 + size).ok_or(RX_ERR_IO)?;
+    if size > out.len() {
+        return Err(RX_ERR_BUFFER_TOO_SMALL);
+    }
+    out[..size].copy_from_slice(digest);
+    Ok(size)
+}
+
+/// Find the TPM
+///
+/// Needs the ACPI tables and the physmap.
+pub fn init() {
+    let tpm: Option<Box<dyn TpmInterface>> = match acpi::tpm2() {
+        Some(table) if table.start_method == tpm2::START_METHOD_CRB => {
+            crb::probe(table.control_area).map(|tpm| Box::new(tpm) as Box<dyn TpmInterface>)
+        }
+        Some(table) if table.start_method == tpm2::START_METHOD_FIFO => {
+            fifo::probe().map(|tpm| Box::new(tpm) as Box<dyn TpmInterface>)
+        }
+        Some(table) => {
+            log_warn!("TPM: unsupported start method {}", table.start_method);
+            None
+        }
+        None => fifo::probe().map(|tpm| Box::new(tpm) as Box<dyn TpmInterface>),
+    };
+
+    let tpm = match tpm {
+        Some(tpm) => tpm,
+        None => return,
+    };
+
+    let log_size = handoff::get().and_then(|h| h.tpm_event_log()).map_or(0, |(_, size)| size);
+    log_info!("TPM: {} interface, {} byte event log", tpm.name(), log_size);
+    *TPM.lock() = Some(tpm);
+}
+
+/// Whether a TPM was found
+pub fn is_present() -> bool {
+    TPM.lock().is_some()
+}
+
+/// Send a marshalled command to the TPM
+///
+/// Returns the response length. The response code is left for the
+/// caller to check.
+pub fn transmit(cmd: &[u8], rsp: &mut [u8]) -> Result<usize> {
+    if cmd.len() < HEADER_SIZE || cmd.len() > MAX_BUFFER {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    match TPM.lock().as_ref() {
+        Some(tpm) => tpm.transmit(cmd, rsp),
+        None => Err(RX_ERR_NOT_SUPPORTED),
+    }
+}
+
+/// Read PCR `index` from the `alg` bank into `out`
+///
+/// Returns the digest length.
+pub fn pcr_read(index: u32, alg: u16, out: &mut [u8]) -> Result<usize> {
+    if index >= PCR_COUNT {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    let mut rsp = [0u8; 128];
+    let len = transmit(&pcr_read_command(index, alg), &mut rsp)?;
+    parse_pcr_read(&rsp[..len], out)
+}
+
+/// The boot event log passed by the loader, in the TCG crypto-agile
+/// format
+///
+/// Events logged after the loader copied it, such as ExitBootServices,
+/// are in the final events table, see `KernelHandoff::tpm_final_events`.
+pub fn event_log() -> Option<&'static [u8]> {
+    let (base, size) = handoff::get()?.tpm_event_log()?;
+    let ptr = mmu::phys_to_virt(base) as *const u8;
+    Some(unsafe { core::slice::from_raw_parts(ptr, size as usize) })
+}
+
+fn cmd_tpm(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    if !is_present() {
+        crate::println!("tpm: none");
+        return 0;
+    }
+
+    let mut digest = [0u8; 64];
+    for index in 0..PCR_COUNT {
+        match pcr_read(index, TPM_ALG_SHA256, &mut digest) {
+            Ok(len) => {
+                crate::print!("PCR {:2}: ", index);
+                for byte in &digest[..len] {
+                    crate::print!("{:02x}", byte);
+                }
+                crate::println!();
+            }
+            Err(err) => {
+                crate::println!("PCR {:2}: error {}", index, err);
+                return -1;
+            }
+        }
+    }
+    if let Some(log) = event_log() {
+        crate::println!("event log: {} bytes", log.len());
+    }
+    0
+}
+
+crate::static_command!("tpm", "dump the SHA-256 PCRs", cmd_tpm);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcr_read_command() {
+        let cmd = pcr_read_command(9, TPM_ALG_SHA256);
+        assert_eq!(
+            cmd,
+            [
+                0x80, 0x01, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x01, 0x7e, 0x00, 0x00, 0x00, 0x01,
+                0x00, 0x0b, 0x03, 0x00, 0x02, 0x00,
+            ]
+        );
+        assert_eq!(response_size(&cmd).unwrap(), 20);
+    }
+
+    #[test]
+    fn test_parse_pcr_read() {
+        let mut rsp = [0u8; 64];
+        rsp[0..2].copy_from_slice(&TPM_ST_NO_SESSIONS.to_be_bytes());
+        let len = rsp.len() as u32;
+        rsp[2..6].copy_from_slice(&len.to_be_bytes());
+        // Update counter, one selection of PCR 9, one 20-byte digest
+        rsp[14..18].copy_from_slice(&1u32.to_be_bytes());
+        rsp[18..20].copy_from_slice(&TPM_ALG_SHA1.to_be_bytes());
+        rsp[20] = 3;
+        rsp[22] = 0x02;
+        rsp[24..28].copy_from_slice(&1u32.to_be_bytes());
+        rsp[28..30].copy_from_slice(&20u16.to_be_bytes());
+        rsp[30..50].fill(0xab);
+
+        let mut out = [0u8; 32];
+        assert_eq!(parse_pcr_read(&rsp, &mut out).unwrap(), 20);
+        assert_eq!(out[..20], [0xab; 20]);
+
+        // Inactive bank: no digests
+        rsp[24..28].copy_from_slice(&0u32.to_be_bytes());
+        assert_eq!(parse_pcr_read(&rsp, &mut out), Err(RX_ERR_NOT_FOUND));
+
+        // Error response code
+        rsp[6..10].copy_from_slice(&0x101u32.to_be_bytes());
+        assert_eq!(parse_pcr_read(&rsp, &mut out), Err(RX_ERR_IO));
+    }
+}
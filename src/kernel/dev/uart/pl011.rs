@@ -262,6 +262,9 @@ fn pl011_irq_handler() {
             let c = unsafe { uart_read(base, UART_DR) as u8 };
             rx_buf.write_char(c);
         }
+
+        // Console input wakes a suspended system
+        crate::kernel::power::suspend::wake();
     }
 
     // Handle TX interrupt
@@ -464,6 +467,46 @@ pub fn pl011_start_panic() {
     UART_TX_IRQ_ENABLED.store(false, Ordering::Release);
 }
 
+// ============================================================================
+// Suspend and Resume
+// ============================================================================
+
+/// Interrupt mask saved by `pl011_suspend`
+static UART_SAVED_IMSC: SpinMutex<u32> = SpinMutex::new(0);
+
+/// Drain the TX FIFO and leave only RX interrupts, as a wake source
+fn pl011_suspend() -> crate::rustux::types::Result {
+    let base = *UART_BASE.lock();
+    if base == 0 {
+        return Ok(());
+    }
+
+    unsafe {
+        while uart_read(base, UART_FR) & (FR_TXFE | FR_BUSY) != FR_TXFE {
+            core::hint::spin_loop();
+        }
+
+        let imsc = uart_read(base, UART_IMSC);
+        *UART_SAVED_IMSC.lock() = imsc;
+        uart_write(base, UART_IMSC, imsc & (IMSC_RX | IMSC_RT));
+    }
+    Ok(())
+}
+
+/// Restore the interrupt mask saved by `pl011_suspend`
+fn pl011_resume() {
+    let base = *UART_BASE.lock();
+    if base == 0 {
+        return;
+    }
+
+    unsafe {
+        uart_write(base, UART_IMSC, *UART_SAVED_IMSC.lock());
+    }
+}
+
+crate::static_pm_ops!("pl011", pl011_suspend, pl011_resume);
+
 // ============================================================================
 // Debug Output Integration
 // ============================================================================}
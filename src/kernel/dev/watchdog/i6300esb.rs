@@ -0,0 +1,193 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Intel 6300ESB Watchdog
+//!
+//! The watchdog function of the 6300ESB I/O hub, PCI device 8086:25ab,
+//! which QEMU provides with `-device i6300esb`. It counts down two stages
+//! at about 1 kHz; the first would raise an interrupt, which is left off,
+//! and the end of the second resets the machine.
+//!
+//! The registers in BAR 0 are locked: every write must be preceded by
+//! the unlock sequence written to the reload register.
+
+use super::HwWatchdog;
+use crate::kernel::dev::pcie::{bus, PciAddrSpace};
+use crate::kernel::mmu;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// PCI IDs
+const VENDOR_INTEL: u16 = 0x8086;
+const DEVICE_6300ESB_WDT: u16 = 0x25ab;
+
+/// Config space registers
+const CONFIG_REG: u16 = 0x60;
+const LOCK_REG: u16 = 0x68;
+
+/// `CONFIG_REG`: no interrupt on the first stage, 1 kHz clock, reset on
+/// the second stage
+const CONFIG_NO_INTERRUPT: u32 = 0x3;
+
+/// `LOCK_REG` bits
+const LOCK_ENABLE: u32 = 1 << 1;
+const LOCK_LOCKED: u32 = 1 << 0;
+
+/// BAR 0 registers
+const REG_TIMER1: usize = 0x00;
+const REG_TIMER2: usize = 0x04;
+const REG_RELOAD: usize = 0x0c;
+
+/// `REG_RELOAD` bits
+const RELOAD_RELOAD: u16 = 1 << 8;
+const RELOAD_TIMEOUT: u16 = 1 << 9;
+
+/// Unlock sequence
+const UNLOCK1: u16 = 0x80;
+const UNLOCK2: u16 = 0x86;
+
+/// Preload values are 20 bits; both stages together take preload / 2^9
+/// seconds
+const PRELOAD_MAX: u64 = 0xf_ffff;
+const PRELOAD_PER_SECOND: u64 = 1 << 9;
+const NS_PER_SECOND: u64 = 1_000_000_000;
+
+/// A 6300ESB watchdog
+pub struct I6300Esb {
+    /// Index of the function in the PCI bus list
+    device: usize,
+
+    /// Virtual address of BAR 0
+    regs: usize,
+
+    /// The reset status bit was set at probe
+    caused_reset: bool,
+}
+
+impl I6300Esb {
+    fn write16(&self, reg: usize, value: u16) {
+        unsafe { core::ptr::write_volatile((self.regs + reg) as *mut u16, value) }
+    }
+
+    fn read16(&self, reg: usize) -> u16 {
+        unsafe { core::ptr::read_volatile((self.regs + reg) as *const u16) }
+    }
+
+    fn write32(&self, reg: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.regs + reg) as *mut u32, value) }
+    }
+
+    fn unlock(&self) {
+        self.write16(REG_RELOAD, UNLOCK1);
+        self.write16(REG_RELOAD, UNLOCK2);
+    }
+
+    fn set_lock(&self, value: u32) -> Result {
+        bus::with_device(self.device, |dev| dev.config_write(LOCK_REG, 1, value))?
+    }
+}
+
+/// Preload per stage for a `timeout` split evenly over both stages
+fn preload_for(timeout: u64) -> u64 {
+    let seconds = timeout.div_ceil(NS_PER_SECOND).max(1);
+    (seconds * PRELOAD_PER_SECOND).min(PRELOAD_MAX)
+}
+
+impl HwWatchdog for I6300Esb {
+    fn name(&self) -> &'static str {
+        "i6300esb"
+    }
+
+    fn max_timeout(&self) -> u64 {
+        PRELOAD_MAX / PRELOAD_PER_SECOND * NS_PER_SECOND
+    }
+
+    fn enable(&self, timeout: u64) -> Result<u64> {
+        let preload = preload_for(timeout);
+        self.unlock();
+        self.write32(REG_TIMER1, preload as u32);
+        self.unlock();
+        self.write32(REG_TIMER2, preload as u32);
+        self.unlock();
+        self.write16(REG_RELOAD, RELOAD_RELOAD);
+        self.set_lock(LOCK_ENABLE)?;
+        Ok(preload / PRELOAD_PER_SECOND * NS_PER_SECOND)
+    }
+
+    fn pet(&self) {
+        self.unlock();
+        self.write16(REG_RELOAD, RELOAD_RELOAD);
+    }
+
+    fn disable(&self) -> Result {
+        self.pet();
+        self.set_lock(0)?;
+        let lock = bus::with_device(self.device, |dev| dev.config_read(LOCK_REG, 1))??;
+        if lock & LOCK_ENABLE != 0 {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        Ok(())
+    }
+
+    fn caused_last_reset(&self) -> bool {
+        self.caused_reset
+    }
+}
+
+/// Find and reset the watchdog, leaving it disabled
+pub fn probe() -> Option<I6300Esb> {
+    let device = (0..bus::device_count()).find(|&index| {
+        bus::with_device(index, |dev| {
+            dev.info.vendor_id == VENDOR_INTEL && dev.info.device_id == DEVICE_6300ESB_WDT
+        })
+        .unwrap_or(false)
+    })?;
+
+    let (bar, lock) = bus::with_device(device, |dev| {
+        let bar = dev.bar(0).filter(|bar| bar.addr_space == PciAddrSpace::MMIO);
+        let _ = dev.config_write(CONFIG_REG, 2, CONFIG_NO_INTERRUPT);
+        (bar, dev.config_read(LOCK_REG, 1).unwrap_or(0))
+    })
+    .ok()?;
+
+    let bar = match bar {
+        Some(bar) => bar,
+        None => {
+            log_warn!("watchdog: i6300esb without a memory BAR");
+            return None;
+        }
+    };
+    if lock & LOCK_LOCKED != 0 {
+        log_warn!("watchdog: i6300esb locked by firmware, can't be stopped");
+    }
+
+    let mut wdt = I6300Esb { device, regs: mmu::phys_to_virt(bar.base), caused_reset: false };
+    let _ = wdt.set_lock(0);
+
+    // Note and clear a timeout left over from the previous boot
+    wdt.unlock();
+    wdt.caused_reset = wdt.read16(REG_RELOAD) & RELOAD_TIMEOUT != 0;
+    wdt.unlock();
+    wdt.write16(REG_RELOAD, RELOAD_TIMEOUT | RELOAD_RELOAD);
+
+    log_info!("watchdog: i6300esb at {:#x}", bar.base);
+    Some(wdt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preload_for() {
+        assert_eq!(preload_for(1), PRELOAD_PER_SECOND);
+        assert_eq!(preload_for(10 * NS_PER_SECOND), 10 * PRELOAD_PER_SECOND);
+        assert_eq!(preload_for(u64::MAX / 2), PRELOAD_MAX);
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Hardware Watchdog Drivers
+//!
+//! A hardware watchdog resets the machine unless it is petted in time. It
+//! is the backstop for hangs the kernel can't see itself, such as a CPU
+//! stuck with interrupts off; the policy around it lives in
+//! [`lib::watchdog`](crate::kernel::lib::watchdog).
+//!
+//! # Drivers
+//!
+//! - [`i6300esb`] - Intel 6300ESB, the PCI watchdog QEMU emulates on x86
+//! - [`sbsa`] - Arm SBSA generic watchdog, from the ACPI GTDT or the
+//!   device tree (arm64 only)
+
+pub mod i6300esb;
+#[cfg(target_arch = "aarch64")]
+pub mod sbsa;
+
+use crate::rustux::types::Result;
+use alloc::boxed::Box;
+
+/// A hardware watchdog driver
+pub trait HwWatchdog: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Longest timeout the hardware supports, in nanoseconds
+    fn max_timeout(&self) -> u64;
+
+    /// Start counting down from `timeout` nanoseconds
+    ///
+    /// The timeout is rounded to what the hardware can do; returns the
+    /// timeout actually set.
+    fn enable(&self, timeout: u64) -> Result<u64>;
+
+    /// Restart the countdown
+    fn pet(&self);
+
+    /// Stop the watchdog, if the hardware allows it
+    fn disable(&self) -> Result;
+
+    /// Whether the watchdog reset the machine on the previous boot
+    fn caused_last_reset(&self) -> bool {
+        false
+    }
+}
+
+/// Find a hardware watchdog
+///
+/// Needs PCI enumeration and the firmware tables. Returns the first one
+/// found, left disabled.
+pub fn probe() -> Option<Box<dyn HwWatchdog>> {
+    #[cfg(target_arch = "aarch64")]
+    if let Some(wdt) = sbsa::probe() {
+        return Some(Box::new(wdt));
+    }
+
+    if let Some(wdt) = i6300esb::probe() {
+        return Some(Box::new(wdt));
+    }
+
+    None
+}
@@ -0,0 +1,146 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Arm SBSA Generic Watchdog
+//!
+//! Two register frames: the control frame holds the enable bit and the
+//! offset register, the refresh frame restarts the countdown on any
+//! write. The watchdog counts at the system counter frequency. When the
+//! offset runs out the first time it raises WS0, an interrupt the kernel
+//! leaves unrouted; the second time it raises WS1, which the platform
+//! wires to reset. The timeout is therefore twice the offset.
+//!
+//! Found from the ACPI GTDT or an `arm,sbsa-gwdt` device tree node.
+
+use super::HwWatchdog;
+use crate::kernel::dev::acpi;
+use crate::kernel::dev::fdt::platform::platform_info;
+use crate::kernel::dev::timer::arm_generic;
+use crate::kernel::mmu;
+use crate::kernel::vm::layout::PAddr;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+// Import logging macros
+use crate::log_info;
+
+/// Control frame registers
+const REG_WCS: usize = 0x000;
+const REG_WOR: usize = 0x008;
+
+/// Refresh frame registers
+const REG_WRR: usize = 0x000;
+
+/// `WCS` bits
+const WCS_EN: u32 = 1 << 0;
+const WCS_WS1: u32 = 1 << 2;
+
+const NS_PER_SECOND: u64 = 1_000_000_000;
+
+/// An SBSA generic watchdog
+pub struct SbsaWatchdog {
+    /// Virtual address of the control frame
+    control: usize,
+
+    /// Virtual address of the refresh frame
+    refresh: usize,
+
+    /// System counter frequency in Hz
+    freq: u64,
+
+    /// WS1 was still asserted at probe
+    caused_reset: bool,
+}
+
+impl SbsaWatchdog {
+    fn read_control(&self, reg: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.control + reg) as *const u32) }
+    }
+
+    fn write_control(&self, reg: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.control + reg) as *mut u32, value) }
+    }
+}
+
+/// Offset register value for `timeout`, which covers both stages
+fn offset_for(timeout: u64, freq: u64) -> u32 {
+    let ticks = (timeout as u128 * freq as u128 / NS_PER_SECOND as u128 / 2).max(1);
+    ticks.min(u32::MAX as u128) as u32
+}
+
+impl HwWatchdog for SbsaWatchdog {
+    fn name(&self) -> &'static str {
+        "sbsa-gwdt"
+    }
+
+    fn max_timeout(&self) -> u64 {
+        (u32::MAX as u128 * 2 * NS_PER_SECOND as u128 / self.freq as u128) as u64
+    }
+
+    fn enable(&self, timeout: u64) -> Result<u64> {
+        let offset = offset_for(timeout, self.freq);
+        self.write_control(REG_WOR, offset);
+        self.pet();
+        self.write_control(REG_WCS, WCS_EN);
+        Ok((offset as u128 * 2 * NS_PER_SECOND as u128 / self.freq as u128) as u64)
+    }
+
+    fn pet(&self) {
+        unsafe { core::ptr::write_volatile((self.refresh + REG_WRR) as *mut u32, 0) }
+    }
+
+    fn disable(&self) -> Result {
+        self.write_control(REG_WCS, 0);
+        if self.read_control(REG_WCS) & WCS_EN != 0 {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        Ok(())
+    }
+
+    fn caused_last_reset(&self) -> bool {
+        self.caused_reset
+    }
+}
+
+/// Find the watchdog and stop it
+pub fn probe() -> Option<SbsaWatchdog> {
+    let (control, refresh) = acpi::gtdt()
+        .and_then(|gtdt| gtdt.watchdogs().first().map(|wd| (wd.control_base, wd.refresh_base)))
+        .or_else(|| platform_info().and_then(|info| info.sbsa_watchdog))?;
+    if control == 0 || refresh == 0 {
+        return None;
+    }
+
+    let freq = arm_generic::timer_frequency() as u64;
+    if freq == 0 {
+        return None;
+    }
+
+    let mut wdt = SbsaWatchdog {
+        control: mmu::phys_to_virt(control as PAddr),
+        refresh: mmu::phys_to_virt(refresh as PAddr),
+        freq,
+        caused_reset: false,
+    };
+    wdt.caused_reset = wdt.read_control(REG_WCS) & WCS_WS1 != 0;
+    let _ = wdt.disable();
+
+    log_info!("watchdog: SBSA generic watchdog at {:#x}", control);
+    Some(wdt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_for() {
+        // 62.5 MHz counter: a 10 s timeout is two 5 s stages
+        assert_eq!(offset_for(10 * NS_PER_SECOND, 62_500_000), 312_500_000);
+        assert_eq!(offset_for(0, 62_500_000), 1);
+        assert_eq!(offset_for(u64::MAX, 62_500_000), u32::MAX);
+    }
+}
@@ -7,35 +7,62 @@
 //! Deferred Procedure Calls
 //!
 //! This module provides Deferred Procedure Call (DPC) support for the Rustux kernel.
-//! DPCs allow work to be deferred to be executed later in a thread context.
+//! DPCs allow work to be deferred out of hard interrupt context.
 //!
 //! # Design
 //!
 //! - **Per-CPU DPC queues**: Each CPU has its own DPC queue
-//! - **Dedicated thread**: Each CPU has a DPC worker thread
-//! - **Deferred execution**: Work can be queued from interrupt context
+//! - **Dedicated thread**: Each CPU has a high-priority DPC worker thread
+//! - **Interrupt exit**: The outermost interrupt exit runs up to
+//!   `DPC_IRQ_EXIT_BUDGET` DPCs before returning, and leaves the rest to
+//!   the worker thread
+//! - **Coalescing**: Queueing a DPC that is already queued is a no-op, so
+//!   any number of queues before it runs cost one callback
 //! - **FIFO ordering**: DPCs executed in order they were queued
 //!
+//! Callbacks may run on interrupt exit with interrupts disabled, so they
+//! must not block.
+//!
 //! # Usage
 //!
+//! A driver's interrupt handler acknowledges the device and queues a DPC
+//! that does the real work, such as draining a receive ring:
+//!
 //! ```rust
-//! let dpc = Dpc::new(|dpc| {
-//!     // Do work
-//! });
+//! static RX_DPC: Dpc = Dpc::with_callback(rx_dpc);
+//!
+//! fn irq_handler() {
+//!     device.ack();
+//!     let _ = dpc_queue(&RX_DPC, false);
+//! }
 //!
-//! // Queue for later execution
-//! dpc.queue(true)?;
+//! unsafe fn rx_dpc(_dpc: &Dpc) {
+//!     while let Some(packet) = device.rx_ring.pop() {
+//!         deliver(packet);
+//!     }
+//! }
 //! ```
+//!
+//! Timer expiry works the same way: the timer interrupt only checks the
+//! next deadline and queues the timer DPC (see [`crate::kernel::timer`]).
 
 
 use crate::kernel::sync::{Event, EventFlags, Mutex};
-use crate::kernel::thread::ThreadId;
+use crate::kernel::thread::{self, ThreadId, ThreadPriority};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
-use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 // Import logging macros
-use crate::{log_debug, log_info};
+use crate::{log_debug, log_error, log_info};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(DPC_QUEUED, "kernel.dpc.queued");
+KCOUNTER!(DPC_COALESCED, "kernel.dpc.coalesced");
+KCOUNTER!(DPC_RUN_IRQ_EXIT, "kernel.dpc.run.irq_exit");
+KCOUNTER!(DPC_RUN_THREAD, "kernel.dpc.run.thread");
 
 /// ============================================================================
 /// DPC
@@ -44,8 +71,11 @@ use crate::{log_debug, log_info};
 /// DPC callback function type
 pub type DpcCallback = unsafe fn(dpc: &Dpc);
 
-/// DPC priority level
-pub const DPC_THREAD_PRIORITY: u8 = 32; // Higher than default
+/// DPC worker thread priority, just below real-time
+pub const DPC_THREAD_PRIORITY: ThreadPriority = thread::PRIORITY_REALTIME - 1;
+
+/// Most DPCs run on one interrupt exit
+pub const DPC_IRQ_EXIT_BUDGET: usize = 8;
 
 /// DPC structure
 ///
@@ -62,6 +92,9 @@ pub struct Dpc {
 
     /// Whether this DPC is currently queued
     pub queued: AtomicBool,
+
+    /// CPU whose queue holds the DPC while it is queued
+    pub cpu: AtomicU32,
 }
 
 unsafe impl Send for Dpc {}
@@ -74,16 +107,18 @@ impl Dpc {
             arg: 0,
             next: Mutex::new(None),
             queued: AtomicBool::new(false),
+            cpu: AtomicU32::new(0),
         }
     }
 
     /// Create a DPC with a callback
-    pub fn with_callback(callback: DpcCallback) -> Self {
+    pub const fn with_callback(callback: DpcCallback) -> Self {
         Self {
             func: Mutex::new(Some(callback)),
             arg: 0,
             next: Mutex::new(None),
             queued: AtomicBool::new(false),
+            cpu: AtomicU32::new(0),
         }
     }
 
@@ -97,51 +132,42 @@ impl Dpc {
         self.arg = arg;
     }
 
-    /// Queue the DPC for execution
+    /// Queue the DPC on the current CPU
+    ///
+    /// Safe from interrupt context. If the DPC is already queued and has
+    /// not started running, this is a no-op: the queued run covers it.
     ///
     /// # Arguments
     ///
     /// * `reschedule` - Whether to trigger immediate rescheduling
-    ///
-    /// # Returns
-    ///
-    /// - `Ok(())` if queued successfully
-    /// - `Err(RX_ERR_ALREADY_EXISTS)` if already queued
     pub fn queue(&self, reschedule: bool) -> Result {
-        // Check if already queued
-        if self.queued.load(Ordering::Acquire) {
-            return Err(RX_ERR_ALREADY_EXISTS);
+        // Claim the DPC; whoever queued it first owns the queue entry
+        if self.queued.swap(true, Ordering::AcqRel) {
+            DPC_COALESCED.add(1);
+            return Ok(());
         }
 
-        // Get current CPU's DPC queue
-        let cpu_num = crate::kernel::percpu::current_cpu_num() as usize;
+        let cpu_num = crate::kernel::percpu::current_cpu_num();
+        self.cpu.store(cpu_num, Ordering::Relaxed);
 
-        // Add to queue
-        unsafe {
-            dpc_queue_cpu(self, cpu_num as u32, reschedule)?;
+        let result = unsafe { dpc_queue_cpu(self, cpu_num, reschedule) };
+        if result.is_err() {
+            self.queued.store(false, Ordering::Release);
         }
-
-        self.queued.store(true, Ordering::Release);
-        Ok(())
+        result
     }
 
     /// Cancel the DPC if queued
     ///
     /// # Returns
     ///
-    /// true if was queued and removed, false otherwise
+    /// true if it was queued and removed before running, false otherwise
     pub fn cancel(&self) -> bool {
         if !self.queued.load(Ordering::Acquire) {
             return false;
         }
 
-        // Remove from queue
-        unsafe {
-            dpc_remove_from_queue(self);
-        }
-
-        self.queued.store(false, Ordering::Release);
-        true
+        unsafe { dpc_remove_from_queue(self) }
     }
 
     /// Execute the DPC callback
@@ -201,6 +227,7 @@ impl DpcQueue {
 
     /// Add a DPC to the tail of the queue
     pub fn push(&mut self, dpc: &'static Dpc) {
+        *dpc.next.lock() = None;
         if let Some(tail) = self.tail {
             *tail.next.lock() = Some(dpc);
         } else {
@@ -222,6 +249,31 @@ impl DpcQueue {
         })
     }
 
+    /// Unlink `dpc` from the queue
+    ///
+    /// Returns false if it isn't in the queue.
+    pub fn remove(&mut self, dpc: &Dpc) -> bool {
+        let mut prev: Option<&'static Dpc> = None;
+        let mut cursor = self.head;
+        while let Some(node) = cursor {
+            let next = *node.next.lock();
+            if core::ptr::eq(node, dpc) {
+                match prev {
+                    Some(prev) => *prev.next.lock() = next,
+                    None => self.head = next,
+                }
+                if next.is_none() {
+                    self.tail = prev;
+                }
+                self.count -= 1;
+                return true;
+            }
+            prev = Some(node);
+            cursor = next;
+        }
+        false
+    }
+
     /// Check if queue is empty
     pub fn is_empty(&self) -> bool {
         self.head.is_none()
@@ -240,9 +292,6 @@ impl DpcQueue {
 /// Per-CPU DPC state array
 static mut DPC_STATES: [DpcState; 256] = [const { DpcState::new() }; 256];
 
-/// DPC lock
-static DPC_LOCK: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
-
 impl DpcState {
     const fn new() -> Self {
         Self {
@@ -259,9 +308,9 @@ impl DpcState {
         // Initialize event
         self.event.init(false, EventFlags::empty());
 
-        // Create DPC worker thread
-        let thread_id = create_dpc_thread(cpu_num);
-        *self.thread_id.lock() = Some(thread_id);
+        // Create DPC worker thread; without one, DPCs only run on
+        // interrupt exit
+        *self.thread_id.lock() = create_dpc_thread(cpu_num);
 
         self.initialized.store(true, Ordering::Release);
 
@@ -291,29 +340,31 @@ impl DpcState {
         log_info!("DPC shutdown for CPU");
     }
 
+    /// Run up to `budget` queued DPCs, returning how many ran
+    ///
+    /// A DPC is marked unqueued before its callback runs, so the callback
+    /// (or an interrupt during it) can queue it again.
+    pub fn run(&self, budget: usize) -> usize {
+        let mut ran = 0;
+        while ran < budget {
+            let Some(dpc) = self.queue.lock().pop() else {
+                break;
+            };
+            dpc.queued.store(false, Ordering::Release);
+            dpc.execute();
+            ran += 1;
+        }
+        ran
+    }
+
     /// Process DPCs from the queue
     ///
     /// Called by the DPC worker thread.
     pub fn process(&self) -> bool {
-        // Pop and execute DPCs
-        loop {
-            let dpc = {
-                let mut queue = self.queue.lock();
-                queue.pop()
-            };
-
-            match dpc {
-                Some(dpc) => {
-                    dpc.queued.store(false, Ordering::Release);
-                    dpc.execute();
-                }
-                None => {
-                    // Queue is empty, unsignal the event
-                    self.event.unsignal();
-                    break;
-                }
-            }
-        }
+        // Unsignal first: a DPC queued while draining signals again
+        self.event.unsignal();
+        let ran = self.run(usize::MAX);
+        DPC_RUN_THREAD.add(ran as i64);
 
         // Check if we should stop
         self.stop.load(Ordering::Acquire)
@@ -344,11 +395,19 @@ pub fn dpc_init() {
     dpc_init_for_cpu();
 }
 
+/// Queue a DPC on the current CPU
+///
+/// See [`Dpc::queue`]; queueing an already-queued DPC coalesces with it.
+pub fn dpc_queue(dpc: &'static Dpc, reschedule: bool) -> Result {
+    dpc.queue(reschedule)
+}
+
 /// Queue a DPC on a specific CPU
 ///
 /// # Safety
 ///
-/// Must be called with appropriate locking.
+/// The caller must have claimed the DPC by setting `queued`, and the DPC
+/// must stay alive until it runs or is cancelled.
 pub unsafe fn dpc_queue_cpu(dpc: &Dpc, cpu_id: u32, reschedule: bool) -> Result {
     if cpu_id >= 256 {
         return Err(RX_ERR_INVALID_ARGS);
@@ -361,6 +420,7 @@ pub unsafe fn dpc_queue_cpu(dpc: &Dpc, cpu_id: u32, reschedule: bool) -> Result
         let mut queue = state.queue.lock();
         queue.push(unsafe { &*(dpc as *const Dpc) });
     }
+    DPC_QUEUED.add(1);
 
     // Signal the event
     if reschedule {
@@ -374,13 +434,42 @@ pub unsafe fn dpc_queue_cpu(dpc: &Dpc, cpu_id: u32, reschedule: bool) -> Result
 
 /// Remove a DPC from its queue
 ///
+/// Returns false if it already left the queue to run.
+///
 /// # Safety
 ///
-/// Must be called with appropriate locking.
-pub unsafe fn dpc_remove_from_queue(dpc: &Dpc) {
-    // In a real implementation, this would search all CPU queues
-    // and remove the DPC. For now, this is a stub.
-    let _ = dpc;
+/// The DPC's `cpu` must name the queue it was put on.
+pub unsafe fn dpc_remove_from_queue(dpc: &Dpc) -> bool {
+    let cpu = dpc.cpu.load(Ordering::Relaxed) as usize;
+    if cpu >= 256 {
+        return false;
+    }
+
+    let removed = DPC_STATES[cpu].queue.lock().remove(dpc);
+    if removed {
+        dpc.queued.store(false, Ordering::Release);
+    }
+    removed
+}
+
+/// Run queued DPCs on the way out of an interrupt
+///
+/// Called from the outermost [`crate::kernel::irq::exit`]. Runs at most
+/// `DPC_IRQ_EXIT_BUDGET` DPCs so a busy device can't hold the CPU in
+/// interrupt context; the worker thread, already signaled, runs the rest.
+pub fn dpc_run_on_irq_exit() {
+    let cpu_num = crate::kernel::percpu::current_cpu_num() as usize;
+    if cpu_num >= 256 {
+        return;
+    }
+
+    let state = unsafe { &DPC_STATES[cpu_num] };
+    if !state.initialized.load(Ordering::Acquire) || state.queue.lock().is_empty() {
+        return;
+    }
+
+    let ran = state.run(DPC_IRQ_EXIT_BUDGET);
+    DPC_RUN_IRQ_EXIT.add(ran as i64);
 }
 
 /// Shutdown DPC for a specific CPU
@@ -411,8 +500,12 @@ pub fn dpc_transition_off_cpu(src_cpu: u32, dst_cpu: u32) {
         let mut dst_queue = dst_state.queue.lock();
 
         while let Some(dpc) = src_queue.pop() {
+            dpc.cpu.store(dst_cpu, Ordering::Relaxed);
             dst_queue.push(dpc);
         }
+        if !dst_queue.is_empty() {
+            dst_state.event.signal();
+        }
 
         // Reset source state
         src_state.stop.store(false, Ordering::Release);
@@ -425,9 +518,7 @@ pub fn dpc_transition_off_cpu(src_cpu: u32, dst_cpu: u32) {
 /// ============================================================================
 
 /// DPC worker thread entry point
-extern "C" fn dpc_worker_thread(_arg: u64) -> ! {
-    let cpu_num = crate::kernel::percpu::current_cpu_num() as usize;
-
+extern "C" fn dpc_worker_thread(cpu_num: usize) -> ! {
     log_debug!("DPC worker thread starting on CPU {}", cpu_num);
 
     loop {
@@ -459,13 +550,23 @@ extern "C" fn dpc_worker_thread(_arg: u64) -> ! {
 }
 
 /// Create DPC worker thread
-fn create_dpc_thread(cpu_num: u32) -> ThreadId {
-    // In a real implementation, this would create a new thread
-    // with the dpc_worker_thread entry point
+///
+/// The thread is pinned to `cpu_num`, whose queue it drains.
+fn create_dpc_thread(cpu_num: u32) -> Option<ThreadId> {
     log_debug!("Creating DPC thread for CPU {}", cpu_num);
 
-    // Stub: return a dummy TID
-    1000 + cpu_num as u64
+    let worker = match thread::Thread::new_kernel(dpc_worker_thread, cpu_num as usize, DPC_THREAD_PRIORITY) {
+        Ok(worker) => worker,
+        Err(err) => {
+            log_error!("DPC: no worker thread for CPU {}: {:?}", cpu_num, err);
+            return None;
+        }
+    };
+    worker.set_cpu_affinity(1 << cpu_num);
+    let tid = worker.tid();
+    worker.start().ok();
+    thread::register_thread(worker.into_ref());
+    Some(tid)
 }
 
 // ============================================================================
@@ -485,6 +586,7 @@ pub fn init() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::boxed::Box;
 
     #[test]
     fn test_dpc_new() {
@@ -513,14 +615,48 @@ mod tests {
         let queue = DpcQueue::new();
         assert!(queue.is_empty());
         assert_eq!(queue.len(), 0);
+    }
 
-        // Note: We can't create static references to stack-allocated DPCs
-        // in modern Rust. This test would need a different approach,
-        // such as using Box::leak or changing the queue implementation.
-        // For now, we'll skip the static reference test.
+    #[test]
+    fn test_dpc_queue_remove() {
+        let a: &'static Dpc = Box::leak(Box::new(Dpc::new()));
+        let b: &'static Dpc = Box::leak(Box::new(Dpc::new()));
+        let c: &'static Dpc = Box::leak(Box::new(Dpc::new()));
+
+        let mut queue = DpcQueue::new();
+        queue.push(a);
+        queue.push(b);
+        queue.push(c);
+
+        assert!(queue.remove(b));
+        assert!(!queue.remove(b));
+        assert!(queue.remove(c));
+        assert_eq!(queue.len(), 1);
+
+        // The tail moved back to `a`, so pushes still land after it
+        queue.push(b);
+        assert!(core::ptr::eq(queue.pop().unwrap(), a));
+        assert!(core::ptr::eq(queue.pop().unwrap(), b));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_dpc_state_run_budget() {
+        static RUNS: AtomicU32 = AtomicU32::new(0);
+        unsafe fn count(_dpc: &Dpc) {
+            RUNS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let state = DpcState::new();
+        for _ in 0..3 {
+            let dpc: &'static Dpc = Box::leak(Box::new(Dpc::with_callback(count)));
+            dpc.queued.store(true, Ordering::Relaxed);
+            state.queue.lock().push(dpc);
+        }
 
-        // Test basic push/pop with non-static references
-        // Note: This would require modifying the DpcQueue to accept
-        // non-static references, which is a larger change.
+        assert_eq!(state.run(2), 2);
+        assert_eq!(state.queue.lock().len(), 1);
+        assert_eq!(state.run(DPC_IRQ_EXIT_BUDGET), 1);
+        assert_eq!(RUNS.load(Ordering::Relaxed), 3);
     }
 }
@@ -0,0 +1,670 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot Handoff
+//!
+//! This module receives the boot information block built by the UEFI
+//! loader. The loader places a single physically-contiguous
+//! `KernelHandoff` structure in memory, exits boot services and jumps to
+//! `kmain` with its address in the first argument register.
+//!
+//! # Layout
+//!
+//! The structure layout is shared with `uefi-loader/src/handoff.rs` and
+//! versioned by `HANDOFF_VERSION`. Optional fields use 0 for "absent".
+//!
+//! Other boot protocols (Multiboot2, Limine, PVH) are normalized into the
+//! same structure by `kernel::boot`, so the rest of the kernel only ever
+//! sees a `KernelHandoff`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! if let Some(handoff) = handoff::get() {
+//!     for range in handoff.memory_ranges() {
+//!         // ...
+//!     }
+//! }
+//! ```
+
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// ============================================================================
+/// Handoff ABI
+/// ============================================================================
+
+/// Handoff magic ("RXHANDOF")
+pub const HANDOFF_MAGIC: u64 = 0x464F_444E_4148_5852;
+
+/// Handoff layout version understood by this kernel
+pub const HANDOFF_VERSION: u32 = 7;
+
+/// Maximum number of memory ranges in the handoff
+pub const HANDOFF_MAX_MEMORY_RANGES: usize = 256;
+
+/// Maximum command line length (including NUL)
+pub const HANDOFF_CMDLINE_MAX: usize = 1024;
+
+/// Maximum number of boot modules (initrd, bootfs images)
+pub const HANDOFF_MAX_MODULES: usize = 16;
+
+/// Maximum module name length (including NUL)
+pub const HANDOFF_MODULE_NAME_MAX: usize = 48;
+
+/// Size of the boot RNG seed buffer
+pub const HANDOFF_RNG_SEED_MAX: usize = 32;
+
+/// Maximum number of EFI runtime services regions
+pub const HANDOFF_MAX_EFI_RUNTIME: usize = 32;
+
+/// Boot flag: the firmware enforces UEFI Secure Boot
+pub const HANDOFF_FLAG_SECURE_BOOT: u32 = 1 << 0;
+
+/// Boot flag: the loader verified the signatures of the kernel and boot
+/// modules before entering the kernel (measured boot)
+pub const HANDOFF_FLAG_VERIFIED: u32 = 1 << 1;
+
+/// Boot flag: the loader measured the kernel, boot modules and command
+/// line into TPM PCRs 8 and 9
+pub const HANDOFF_FLAG_MEASURED: u32 = 1 << 2;
+
+/// Memory range type
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryType {
+    /// Usable RAM (includes reclaimed boot-services memory)
+    Available = 1,
+
+    /// Reserved by firmware
+    Reserved = 2,
+
+    /// ACPI tables, reclaimable after parsing
+    Reclaimable = 3,
+
+    /// Memory-mapped I/O
+    Peripheral = 4,
+
+    /// RAM preserved across warm reboot, used for the crash log
+    Persistent = 5,
+}
+
+/// Physical memory range
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryRange {
+    /// Physical base address
+    pub base: u64,
+
+    /// Length in bytes
+    pub length: u64,
+
+    /// Range type
+    pub mem_type: MemoryType,
+
+    /// Reserved, must be zero
+    pub reserved: u32,
+}
+
+/// Region of the EFI memory map used by runtime services
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct EfiRuntimeRange {
+    /// Physical base address
+    pub phys_start: u64,
+
+    /// Length in 4 KiB pages
+    pub page_count: u64,
+
+    /// EFI_MEMORY_* attribute bits
+    pub attribute: u64,
+
+    /// EFI memory type
+    pub efi_type: u32,
+
+    /// Reserved, must be zero
+    pub reserved: u32,
+}
+
+impl EfiRuntimeRange {
+    /// Empty region slot
+    pub const EMPTY: Self = Self {
+        phys_start: 0,
+        page_count: 0,
+        attribute: 0,
+        efi_type: 0,
+        reserved: 0,
+    };
+}
+
+/// Framebuffer pixel format
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferFormat {
+    /// No framebuffer
+    None = 0,
+
+    /// 32-bit RGBX
+    RGB = 1,
+
+    /// 32-bit BGRX
+    BGR = 2,
+}
+
+/// Framebuffer description
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FramebufferInfo {
+    /// Physical base address
+    pub base: u64,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// Pixels per scan line
+    pub stride: u32,
+
+    /// Pixel format
+    pub format: FramebufferFormat,
+}
+
+/// Boot module (file loaded alongside the kernel)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BootModule {
+    /// Physical base address
+    pub base: u64,
+
+    /// Length in bytes
+    pub length: u64,
+
+    /// NUL-terminated name or module command line
+    pub name: [u8; HANDOFF_MODULE_NAME_MAX],
+}
+
+impl BootModule {
+    /// Empty module slot
+    pub const EMPTY: Self = Self {
+        base: 0,
+        length: 0,
+        name: [0; HANDOFF_MODULE_NAME_MAX],
+    };
+
+    /// Get the module name as a string
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
+
+/// Boot information handed to the kernel by the loader
+#[repr(C)]
+pub struct KernelHandoff {
+    /// Must be `HANDOFF_MAGIC`
+    pub magic: u64,
+
+    /// Layout version
+    pub version: u32,
+
+    /// Size of the structure in bytes
+    pub size: u32,
+
+    /// Physical address of the ACPI RSDP (0 if absent)
+    pub acpi_rsdp: u64,
+
+    /// Physical address of the SMBIOS entry point (0 if absent)
+    pub smbios_entry: u64,
+
+    /// EFI system table (0 if not booted via UEFI)
+    pub system_table: u64,
+
+    /// Physical load address of the kernel image
+    pub kernel_base: u64,
+
+    /// Size of the loaded kernel image
+    pub kernel_size: u64,
+
+    /// Framebuffer (format == None if absent)
+    pub framebuffer: FramebufferInfo,
+
+    /// Number of valid memory ranges
+    pub memory_range_count: u32,
+
+    /// Command line length excluding NUL
+    pub cmdline_len: u32,
+
+    /// Memory ranges, sorted by base address
+    pub memory_map: [MemoryRange; HANDOFF_MAX_MEMORY_RANGES],
+
+    /// NUL-terminated ASCII command line
+    pub cmdline: [u8; HANDOFF_CMDLINE_MAX],
+
+    /// Number of valid boot modules
+    pub module_count: u32,
+
+    /// Reserved, must be zero
+    pub reserved0: u32,
+
+    /// Boot modules
+    pub modules: [BootModule; HANDOFF_MAX_MODULES],
+
+    /// Physical address of the flattened device tree (0 if absent)
+    pub devicetree: u64,
+
+    /// Distance the kernel was loaded from its linked base (0 if not moved)
+    pub kernel_slide: u64,
+
+    /// Number of valid bytes in `rng_seed`
+    pub rng_seed_len: u32,
+
+    /// `HANDOFF_FLAG_*` bits describing how the kernel was booted
+    pub boot_flags: u32,
+
+    /// Random bytes from the firmware RNG or RDRAND
+    pub rng_seed: [u8; HANDOFF_RNG_SEED_MAX],
+
+    /// Physical address of the TPM 2.0 event log, crypto-agile format
+    /// (0 if absent)
+    pub tpm_event_log: u64,
+
+    /// Length of the TPM event log in bytes
+    pub tpm_event_log_size: u64,
+
+    /// Physical address of the EFI_TCG2_FINAL_EVENTS_TABLE, holding events
+    /// logged after the loader copied the log (0 if absent)
+    pub tpm_final_events: u64,
+
+    /// Number of valid EFI runtime regions
+    pub efi_runtime_count: u32,
+
+    /// Reserved, must be zero
+    pub reserved1: u32,
+
+    /// Memory map entries the firmware marked EFI_MEMORY_RUNTIME, with
+    /// their EFI types and attributes
+    pub efi_runtime: [EfiRuntimeRange; HANDOFF_MAX_EFI_RUNTIME],
+}
+
+impl KernelHandoff {
+    /// Create an empty, valid handoff block
+    ///
+    /// Used by `kernel::boot` when normalizing non-UEFI boot protocols.
+    pub const fn empty() -> Self {
+        const EMPTY_RANGE: MemoryRange = MemoryRange {
+            base: 0,
+            length: 0,
+            mem_type: MemoryType::Reserved,
+            reserved: 0,
+        };
+
+        Self {
+            magic: HANDOFF_MAGIC,
+            version: HANDOFF_VERSION,
+            size: core::mem::size_of::<KernelHandoff>() as u32,
+            acpi_rsdp: 0,
+            smbios_entry: 0,
+            system_table: 0,
+            kernel_base: 0,
+            kernel_size: 0,
+            framebuffer: FramebufferInfo {
+                base: 0,
+                size: 0,
+                width: 0,
+                height: 0,
+                stride: 0,
+                format: FramebufferFormat::None,
+            },
+            memory_range_count: 0,
+            cmdline_len: 0,
+            memory_map: [EMPTY_RANGE; HANDOFF_MAX_MEMORY_RANGES],
+            cmdline: [0; HANDOFF_CMDLINE_MAX],
+            module_count: 0,
+            reserved0: 0,
+            modules: [BootModule::EMPTY; HANDOFF_MAX_MODULES],
+            devicetree: 0,
+            kernel_slide: 0,
+            rng_seed_len: 0,
+            boot_flags: 0,
+            rng_seed: [0; HANDOFF_RNG_SEED_MAX],
+            tpm_event_log: 0,
+            tpm_event_log_size: 0,
+            tpm_final_events: 0,
+            efi_runtime_count: 0,
+            reserved1: 0,
+            efi_runtime: [EfiRuntimeRange::EMPTY; HANDOFF_MAX_EFI_RUNTIME],
+        }
+    }
+
+    /// Add a memory range, keeping the map sorted and coalesced
+    ///
+    /// Returns false if the map is full.
+    pub fn push_memory_range(&mut self, base: u64, length: u64, mem_type: MemoryType) -> bool {
+        if length == 0 {
+            return true;
+        }
+
+        let count = self.memory_range_count as usize;
+
+        // Find insertion point (ranges are sorted by base)
+        let mut pos = count;
+        while pos > 0 && self.memory_map[pos - 1].base > base {
+            pos -= 1;
+        }
+
+        // Merge with the previous range if contiguous and same type
+        if pos > 0 {
+            let prev = &mut self.memory_map[pos - 1];
+            if prev.mem_type == mem_type && prev.base + prev.length == base {
+                prev.length += length;
+                return true;
+            }
+        }
+
+        if count >= HANDOFF_MAX_MEMORY_RANGES {
+            return false;
+        }
+
+        let mut i = count;
+        while i > pos {
+            self.memory_map[i] = self.memory_map[i - 1];
+            i -= 1;
+        }
+        self.memory_map[pos] = MemoryRange { base, length, mem_type, reserved: 0 };
+        self.memory_range_count += 1;
+        true
+    }
+
+    /// Set the command line from raw bytes (stops at NUL, truncates)
+    pub fn set_cmdline(&mut self, bytes: &[u8]) {
+        let mut len = 0;
+        for &c in bytes {
+            if c == 0 || len >= HANDOFF_CMDLINE_MAX - 1 {
+                break;
+            }
+            self.cmdline[len] = if c.is_ascii() { c } else { b'.' };
+            len += 1;
+        }
+        self.cmdline[len] = 0;
+        self.cmdline_len = len as u32;
+    }
+
+    /// Add a boot module
+    ///
+    /// Returns false if the module table is full.
+    pub fn push_module(&mut self, base: u64, length: u64, name: &[u8]) -> bool {
+        let count = self.module_count as usize;
+        if count >= HANDOFF_MAX_MODULES {
+            return false;
+        }
+
+        let mut module = BootModule { base, length, name: [0; HANDOFF_MODULE_NAME_MAX] };
+        let n = name.iter()
+            .take(HANDOFF_MODULE_NAME_MAX - 1)
+            .take_while(|&&c| c != 0)
+            .count();
+        module.name[..n].copy_from_slice(&name[..n]);
+
+        self.modules[count] = module;
+        self.module_count += 1;
+        true
+    }
+
+    /// Get the device tree address, if present
+    pub fn devicetree(&self) -> Option<u64> {
+        if self.devicetree != 0 { Some(self.devicetree) } else { None }
+    }
+
+    /// Get the boot RNG seed (empty if the loader had none)
+    pub fn rng_seed(&self) -> &[u8] {
+        &self.rng_seed[..(self.rng_seed_len as usize).min(HANDOFF_RNG_SEED_MAX)]
+    }
+
+    /// Whether the firmware booted with UEFI Secure Boot enforced
+    pub fn secure_boot(&self) -> bool {
+        self.boot_flags & HANDOFF_FLAG_SECURE_BOOT != 0
+    }
+
+    /// Whether the loader verified the kernel and boot module signatures
+    pub fn verified_boot(&self) -> bool {
+        self.boot_flags & HANDOFF_FLAG_VERIFIED != 0
+    }
+
+    /// Whether the loader measured the boot images into the TPM
+    pub fn measured_boot(&self) -> bool {
+        self.boot_flags & HANDOFF_FLAG_MEASURED != 0
+    }
+
+    /// Get the TPM event log as (physical address, length), if present
+    pub fn tpm_event_log(&self) -> Option<(u64, u64)> {
+        if self.tpm_event_log != 0 && self.tpm_event_log_size != 0 {
+            Some((self.tpm_event_log, self.tpm_event_log_size))
+        } else {
+            None
+        }
+    }
+
+    /// Get the TPM final events table address, if present
+    pub fn tpm_final_events(&self) -> Option<u64> {
+        if self.tpm_final_events != 0 { Some(self.tpm_final_events) } else { None }
+    }
+
+    /// Get the EFI runtime services regions
+    pub fn efi_runtime(&self) -> &[EfiRuntimeRange] {
+        let count = (self.efi_runtime_count as usize).min(HANDOFF_MAX_EFI_RUNTIME);
+        &self.efi_runtime[..count]
+    }
+
+    /// Get the valid boot modules
+    pub fn modules(&self) -> &[BootModule] {
+        &self.modules[..self.module_count as usize]
+    }
+
+    /// Check magic, version and size
+    pub fn is_valid(&self) -> bool {
+        self.magic == HANDOFF_MAGIC
+            && self.version == HANDOFF_VERSION
+            && self.size as usize == core::mem::size_of::<KernelHandoff>()
+            && self.memory_range_count as usize <= HANDOFF_MAX_MEMORY_RANGES
+            && (self.cmdline_len as usize) < HANDOFF_CMDLINE_MAX
+            && self.module_count as usize <= HANDOFF_MAX_MODULES
+    }
+
+    /// Get the valid memory ranges
+    pub fn memory_ranges(&self) -> &[MemoryRange] {
+        &self.memory_map[..self.memory_range_count as usize]
+    }
+
+    /// Get the command line as a string
+    pub fn cmdline(&self) -> &str {
+        let bytes = &self.cmdline[..self.cmdline_len as usize];
+        core::str::from_utf8(bytes).unwrap_or("")
+    }
+
+    /// Get the ACPI RSDP physical address
+    pub fn acpi_rsdp(&self) -> Option<u64> {
+        if self.acpi_rsdp != 0 { Some(self.acpi_rsdp) } else { None }
+    }
+
+    /// Get the SMBIOS entry point physical address
+    pub fn smbios_entry(&self) -> Option<u64> {
+        if self.smbios_entry != 0 { Some(self.smbios_entry) } else { None }
+    }
+
+    /// Get the framebuffer, if one was provided
+    pub fn framebuffer(&self) -> Option<&FramebufferInfo> {
+        if self.framebuffer.format != FramebufferFormat::None && self.framebuffer.base != 0 {
+            Some(&self.framebuffer)
+        } else {
+            None
+        }
+    }
+
+    /// Total bytes of available memory
+    pub fn total_available(&self) -> u64 {
+        self.memory_ranges()
+            .iter()
+            .filter(|r| r.mem_type == MemoryType::Available)
+            .map(|r| r.length)
+            .sum()
+    }
+}
+
+/// ============================================================================
+/// Global Handoff
+/// ============================================================================
+
+/// Handoff block from the loader (null if none / invalid)
+static HANDOFF: AtomicPtr<KernelHandoff> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Record the handoff pointer passed to `kmain`
+///
+/// Invalid or missing blocks are ignored so the kernel can still boot
+/// (e.g. under QEMU `-kernel` with no loader).
+///
+/// # Safety
+///
+/// `ptr` must be null or point at memory that stays mapped for the
+/// lifetime of the kernel.
+pub unsafe fn set(ptr: *const KernelHandoff) {
+    if ptr.is_null() {
+        return;
+    }
+
+    if !(*ptr).is_valid() {
+        return;
+    }
+
+    HANDOFF.store(ptr as *mut KernelHandoff, Ordering::Release);
+}
+
+/// Get the handoff block, if the loader provided a valid one
+pub fn get() -> Option<&'static KernelHandoff> {
+    let ptr = HANDOFF.load(Ordering::Acquire);
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { &*ptr })
+    }
+}
+
+/// Feed handoff data into early kernel subsystems
+///
+/// Called from `init_early` once logging is available.
+pub fn init() {
+    let handoff = match get() {
+        Some(h) => h,
+        None => {
+            log_warn!("No boot handoff from loader");
+            return;
+        }
+    };
+
+    // Boot arguments go through the normal cmdline parser
+    if handoff.cmdline_len > 0 {
+        crate::kernel::cmdline::cmdline_append(handoff.cmdline());
+    }
+
+    log_info!("Boot handoff v{} ({}):", handoff.version, crate::kernel::boot::protocol().name());
+    log_info!("  Kernel: {:#x} ({} bytes, slide {:#x})",
+        handoff.kernel_base, handoff.kernel_size, handoff.kernel_slide);
+    log_info!("  Memory ranges: {} ({} MB available)",
+        handoff.memory_range_count, handoff.total_available() / (1024 * 1024));
+    if let Some(rsdp) = handoff.acpi_rsdp() {
+        log_info!("  ACPI RSDP: {:#x}", rsdp);
+    }
+    if let Some(smbios) = handoff.smbios_entry() {
+        log_info!("  SMBIOS: {:#x}", smbios);
+    }
+    if let Some(dtb) = handoff.devicetree() {
+        log_info!("  Device tree: {:#x}", dtb);
+    }
+    log_info!("  Secure Boot: {}, images verified: {}, measured: {}",
+        if handoff.secure_boot() { "on" } else { "off" },
+        if handoff.verified_boot() { "yes" } else { "no" },
+        if handoff.measured_boot() { "yes" } else { "no" });
+    if let Some((log, size)) = handoff.tpm_event_log() {
+        log_info!("  TPM event log: {:#x} ({} bytes)", log, size);
+    }
+    if handoff.efi_runtime_count > 0 {
+        log_info!("  EFI runtime regions: {}", handoff.efi_runtime_count);
+    }
+    for module in handoff.modules() {
+        log_info!("  Module: {:#x} ({} bytes) {}", module.base, module.length, module.name());
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handoff_layout() {
+        // Must match uefi-loader's KernelHandoff
+        assert_eq!(core::mem::size_of::<MemoryRange>(), 24);
+        assert_eq!(core::mem::size_of::<FramebufferInfo>(), 32);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, framebuffer), 56);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, memory_map), 96);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, module_count), 7264);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, modules), 7272);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, devicetree), 8296);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, kernel_slide), 8304);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, boot_flags), 8316);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, rng_seed), 8320);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, tpm_event_log), 8352);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, tpm_final_events), 8368);
+        assert_eq!(core::mem::size_of::<EfiRuntimeRange>(), 32);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, efi_runtime_count), 8376);
+        assert_eq!(memoffset::offset_of!(KernelHandoff, efi_runtime), 8384);
+        assert_eq!(core::mem::size_of::<KernelHandoff>(), 9408);
+    }
+
+    #[test]
+    fn test_handoff_push_memory_range_sorted_coalesced() {
+        let mut h = KernelHandoff::empty();
+        assert!(h.push_memory_range(0x20_0000, 0x10_0000, MemoryType::Available));
+        assert!(h.push_memory_range(0x0, 0x10_0000, MemoryType::Available));
+        assert!(h.push_memory_range(0x30_0000, 0x1000, MemoryType::Available));
+        assert!(h.push_memory_range(0x10_0000, 0x1000, MemoryType::Reserved));
+
+        let ranges = h.memory_ranges();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].base, 0x0);
+        assert_eq!(ranges[1].mem_type, MemoryType::Reserved);
+        assert_eq!(ranges[2].base, 0x20_0000);
+        assert_eq!(ranges[2].length, 0x10_1000);
+        assert!(h.is_valid());
+    }
+
+    #[test]
+    fn test_handoff_modules_and_cmdline() {
+        let mut h = KernelHandoff::empty();
+        h.set_cmdline(b"kernel.fbcon=false\0junk");
+        assert_eq!(h.cmdline(), "kernel.fbcon=false");
+
+        assert!(h.push_module(0x1000, 42, b"bootfs.img\0"));
+        assert_eq!(h.modules().len(), 1);
+        assert_eq!(h.modules()[0].name(), "bootfs.img");
+    }
+
+    #[test]
+    fn test_handoff_null_ignored() {
+        unsafe { set(core::ptr::null()) };
+        assert!(get().is_none());
+    }
+}
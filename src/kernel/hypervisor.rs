@@ -6,16 +6,64 @@
 
 //! Hypervisor Support
 //!
-//! This module provides hypervisor/virtualization support for the Rustux kernel.
+//! A [`Guest`] is a virtual machine: a guest-physical address space built
+//! from VMO ranges, plus the traps its VMM asked for. A [`Vcpu`] runs guest
+//! code on the CPU's virtualization extensions and turns each exit the
+//! kernel can't handle by itself into a port packet for the VMM.
+//!
+//! # Exits
+//!
+//! - Faults on guest memory are resolved here: the backing VMO page is
+//!   committed, mapped into the guest's extended page tables and the guest
+//!   resumes.
+//! - Accesses to `MMIO` traps, writes to `WRITE` traps and accesses to `IO`
+//!   traps stop [`Vcpu::enter`], which returns a `GuestMem` or `GuestIo`
+//!   packet describing the access. The VMM emulates it and enters again.
+//!   The kernel decodes and skips `MOV` instructions; for anything else the
+//!   packet's `access_size` is 0 and the VMM moves the guest past it.
+//! - Writes to `BELL` traps are queued on the trap's port as `GuestBell`
+//!   packets and the guest resumes without waiting for the VMM.
+//! - A guest that halts with no interrupt to take blocks `enter` until
+//!   [`Vcpu::interrupt`] or [`Vcpu::kick`].
+//! - [`Vcpu::kick`] makes `enter` return a `GuestVcpu` interrupt packet the
+//!   next time the vCPU leaves the guest, which it does on every host
+//!   interrupt and at the end of every VMX-preemption timer slice.
+//!
+//! # Architecture Support
+//!
+//! Guest entry is implemented for Intel VMX with extended page tables
+//! (`arch::amd64::vmx`), behind the `hypervisor` feature. Guests start in
+//! 64-bit mode. Elsewhere guests can be created and populated, but creating
+//! a vCPU fails with `RX_ERR_NOT_SUPPORTED`.
 
 
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use core::convert::TryFrom;
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::vmo::Vmo;
+use crate::kernel::sched;
 use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::sync::Mutex;
+use crate::kernel::syscalls::port::{
+    self, guest_vcpu_kind, GuestBellData, GuestIoData, GuestMemData, GuestVcpuData,
+    PacketPayload, PacketType, PortPacket,
+};
+use crate::kernel::thread::{self, BlockReason, ThreadId};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
+use crate::KCOUNTER;
+
+#[cfg(all(target_arch = "x86_64", feature = "hypervisor"))]
+use crate::kernel::arch::amd64::vmx::{VmxGuest as ArchGuest, VmxVcpu as ArchVcpu};
+
+#[cfg(not(all(target_arch = "x86_64", feature = "hypervisor")))]
+use self::unsupported::{ArchGuest, ArchVcpu};
+
+KCOUNTER!(VCPU_EXITS, "hypervisor.vcpu.exits");
+KCOUNTER!(VCPU_PACKETS, "hypervisor.vcpu.packets");
+KCOUNTER!(GUEST_PAGE_FAULTS, "hypervisor.guest.page_faults");
 
 /// Maximum number of guests
 pub const MAX_GUESTS: usize = 64;
@@ -23,116 +71,519 @@ pub const MAX_GUESTS: usize = 64;
 /// Maximum number of VCPUs per guest
 pub const MAX_VCPUS: usize = 8;
 
+/// Size of a guest-physical address space
+pub const GUEST_PHYS_SIZE: u64 = 1 << 39;
+
+/// Guest memory is mapped and trapped in whole pages
+const PAGE_SIZE: u64 = 4096;
+
+/// Number of I/O ports an `IO` trap can cover
+const IO_PORT_COUNT: u64 = 0x10000;
+
+/// ============================================================================
+/// Traps and Mappings
+/// ============================================================================
+
+/// Trap kinds
+pub mod trap_kind {
+    /// Guest writes are queued on a port; the guest doesn't wait
+    pub const BELL: u32 = 0;
+
+    /// Memory-mapped I/O trap
+    pub const MMIO: u32 = 1;
+
+    /// Input/output port trap
+    pub const IO: u32 = 2;
+
+    /// Write protection trap: reads go to guest memory, writes trap
+    pub const WRITE: u32 = 3;
+}
+
+/// Guest memory mapping flags
+pub mod guest_map_flags {
+    /// Guest may read
+    pub const READ: u32 = 0x01;
+
+    /// Guest may write
+    pub const WRITE: u32 = 0x02;
+
+    /// Guest may execute
+    pub const EXECUTE: u32 = 0x04;
+
+    /// All valid flags
+    pub const ALL: u32 = READ | WRITE | EXECUTE;
+}
+
+/// Whether `[addr, addr + len)` is nonempty, page-aligned and inside `limit`
+fn page_range_valid(addr: u64, len: u64, limit: u64) -> bool {
+    len != 0
+        && addr % PAGE_SIZE == 0
+        && len % PAGE_SIZE == 0
+        && addr.checked_add(len).map_or(false, |end| end <= limit)
+}
+
+/// VMO range backing part of a guest-physical address space
+struct GuestRegion {
+    /// Backing VMO
+    vmo: Arc<Vmo>,
+
+    /// Offset of the region in the VMO
+    vmo_offset: usize,
+
+    /// Length in bytes
+    len: usize,
+
+    /// `guest_map_flags`
+    flags: u32,
+}
+
 /// Guest Physical Address Space
-#[repr(C)]
+///
+/// Ranges of VMOs mapped at guest-physical addresses. Pages are committed
+/// and handed to the extended page tables one fault at a time (see
+/// [`Guest::fault`]), so a large, sparse guest costs only what it touches.
 pub struct GuestPhysicalAddressSpace {
-    base: PAddr,
-    size: usize,
+    /// Size in bytes
+    size: u64,
+
+    /// Regions by guest-physical base address
+    regions: SpinMutex<BTreeMap<u64, GuestRegion>>,
 }
 
 impl GuestPhysicalAddressSpace {
-    pub fn new(_base: PAddr, _size: usize) -> core::result::Result<Self, RxError> {
+    /// Create an empty address space of `size` bytes
+    pub fn new(size: u64) -> Result<Self> {
+        if size == 0 || size % PAGE_SIZE != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
         Ok(Self {
-            base: 0,
-            size: 0,
+            size,
+            regions: SpinMutex::new(BTreeMap::new()),
         })
     }
+
+    /// Size in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Back `[addr, addr + len)` with `vmo` from `vmo_offset`
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_INVALID_ARGS` - unaligned or empty range, or bad flags
+    /// - `RX_ERR_OUT_OF_RANGE` - the range passes the end of the address
+    ///   space or of the VMO
+    /// - `RX_ERR_ALREADY_EXISTS` - the range overlaps an existing mapping
+    pub fn map(&self, addr: u64, vmo: Arc<Vmo>, vmo_offset: usize, len: usize, flags: u32) -> Result {
+        if flags & !guest_map_flags::ALL != 0 || flags & guest_map_flags::READ == 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if len == 0 || addr % PAGE_SIZE != 0 || len as u64 % PAGE_SIZE != 0
+            || vmo_offset as u64 % PAGE_SIZE != 0
+        {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if !page_range_valid(addr, len as u64, self.size)
+            || vmo_offset.checked_add(len).map_or(true, |end| end > vmo.size())
+        {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        let mut regions = self.regions.lock();
+        let end = addr + len as u64;
+        if let Some((&base, region)) = regions.range(..end).next_back() {
+            if base + region.len as u64 > addr {
+                return Err(RX_ERR_ALREADY_EXISTS);
+            }
+        }
+        regions.insert(addr, GuestRegion { vmo, vmo_offset, len, flags });
+        Ok(())
+    }
+
+    /// Remove every mapping in `[addr, addr + len)`
+    ///
+    /// Mappings must lie wholly inside or outside the range; nothing is
+    /// removed if one straddles it.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_INVALID_ARGS` - unaligned or empty range, or a mapping
+    ///   straddles it
+    /// - `RX_ERR_NOT_FOUND` - nothing is mapped in the range
+    pub fn unmap(&self, addr: u64, len: usize) -> Result {
+        if !page_range_valid(addr, len as u64, self.size) {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        let mut regions = self.regions.lock();
+        let end = addr + len as u64;
+        if let Some((&base, region)) = regions.range(..addr).next_back() {
+            if base + region.len as u64 > addr {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+        }
+        let inside: alloc::vec::Vec<u64> = regions.range(addr..end).map(|(&base, _)| base).collect();
+        if let Some(&last) = inside.last() {
+            if last + regions[&last].len as u64 > end {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+        } else {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+        for base in inside {
+            regions.remove(&base);
+        }
+        Ok(())
+    }
+
+    /// Commit the page backing `addr`
+    ///
+    /// Returns the page's physical address and whether the guest may
+    /// write it.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_NOT_FOUND` - nothing is mapped at `addr`
+    /// - `RX_ERR_ACCESS_DENIED` - `write` is set and the mapping is read-only
+    pub fn fault(&self, addr: u64, write: bool) -> Result<(PAddr, bool)> {
+        let (vmo, index, writable) = {
+            let regions = self.regions.lock();
+            let (&base, region) = regions.range(..=addr).next_back().ok_or(RX_ERR_NOT_FOUND)?;
+            let offset = addr - base;
+            if offset >= region.len as u64 {
+                return Err(RX_ERR_NOT_FOUND);
+            }
+            let writable = region.flags & guest_map_flags::WRITE != 0;
+            if write && !writable {
+                return Err(RX_ERR_ACCESS_DENIED);
+            }
+            let index = (region.vmo_offset + offset as usize) / PAGE_SIZE as usize;
+            (region.vmo.clone(), index, writable)
+        };
+
+        let paddr = vmo.commit_page_paddr(index)?;
+        Ok((paddr, writable))
+    }
 }
 
 /// Trap entry for handling guest exits
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct Trap {
+    /// One of `trap_kind`
     pub kind: u32,
-    pub addr: VAddr,
+
+    /// Guest-physical address, or first port for `IO`
+    pub addr: u64,
+
+    /// Length in bytes, or number of ports for `IO`
     pub len: usize,
-    pub port: u16,
+
+    /// Port that `BELL` packets are queued on
+    pub port: Option<u64>,
+
+    /// Key of the packets the trap generates
     pub key: u64,
 }
 
+impl Trap {
+    /// Whether the trap covers I/O ports rather than guest memory
+    pub fn is_io(&self) -> bool {
+        self.kind == trap_kind::IO
+    }
+}
+
 /// Trap map for handling guest exits
+///
+/// Memory traps and I/O port traps are kept apart, since the same number
+/// can be both an address and a port.
 #[repr(C)]
 pub struct TrapMap {
-    traps: SpinMutex<BTreeMap<u64, Trap>>,
+    /// Memory traps by guest-physical base address
+    mem: SpinMutex<BTreeMap<u64, Trap>>,
+
+    /// I/O port traps by first port
+    io: SpinMutex<BTreeMap<u64, Trap>>,
 }
 
 impl TrapMap {
     pub fn new() -> Self {
         Self {
-            traps: SpinMutex::new(BTreeMap::new()),
+            mem: SpinMutex::new(BTreeMap::new()),
+            io: SpinMutex::new(BTreeMap::new()),
         }
     }
 
-    pub fn insert(&self, key: u64, trap: Trap) -> Result<()> {
-        let mut traps = self.traps.lock();
-        traps.insert(key, trap);
+    fn space(&self, io: bool) -> &SpinMutex<BTreeMap<u64, Trap>> {
+        if io {
+            &self.io
+        } else {
+            &self.mem
+        }
+    }
+
+    /// Add a trap
+    ///
+    /// Fails with `RX_ERR_ALREADY_EXISTS` if it overlaps another trap.
+    pub fn insert(&self, trap: Trap) -> Result<()> {
+        let mut traps = self.space(trap.is_io()).lock();
+        let end = trap.addr + trap.len as u64;
+        if let Some((_, prev)) = traps.range(..end).next_back() {
+            if prev.addr + prev.len as u64 > trap.addr {
+                return Err(RX_ERR_ALREADY_EXISTS);
+            }
+        }
+        traps.insert(trap.addr, trap);
         Ok(())
     }
 
-    pub fn remove(&self, key: u64) -> core::result::Result<Option<Trap>, RxError> {
-        let mut traps = self.traps.lock();
-        Ok(traps.remove(&key))
+    /// Find the trap covering `addr`, a port if `io` is set
+    pub fn find(&self, io: bool, addr: u64) -> Option<Trap> {
+        let traps = self.space(io).lock();
+        traps
+            .range(..=addr)
+            .next_back()
+            .map(|(_, trap)| *trap)
+            .filter(|trap| addr - trap.addr < trap.len as u64)
+    }
+
+    /// Remove the trap starting at `addr`
+    pub fn remove(&self, io: bool, addr: u64) -> core::result::Result<Option<Trap>, RxError> {
+        let mut traps = self.space(io).lock();
+        Ok(traps.remove(&addr))
     }
 }
 
-/// ID Allocator for managing IDs
-#[repr(C)]
-pub struct IdAllocator<T, const N: usize>
-where
-    T: Copy + Clone + PartialEq,
-{
-    next_id: SpinMutex<u64>,
-    max_id: u64,
-    bitmap: SpinMutex<[u64; N]>,
-    _phantom: core::marker::PhantomData<T>,
+/// ============================================================================
+/// Guests
+/// ============================================================================
+
+/// Guest identifier
+pub type GuestId = u64;
+
+/// Next guest ID counter
+static NEXT_GUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// What a guest memory fault resolved to
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    /// Map `paddr` at the faulting page and resume the guest
+    Map { paddr: PAddr, writable: bool },
+
+    /// The access hit a trap
+    Trap(Trap),
 }
 
-impl<T, const N: usize> IdAllocator<T, N>
-where
-    T: Copy + Clone + PartialEq + TryFrom<u64>,
-{
-    pub fn new() -> Self {
-        Self {
-            next_id: SpinMutex::new(1),
-            max_id: (N * 64) as u64,
-            bitmap: SpinMutex::new([0u64; N]),
-            _phantom: core::marker::PhantomData,
-        }
+/// Guest VM
+pub struct Guest {
+    /// Guest ID
+    pub id: GuestId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// Guest-physical memory
+    gpas: GuestPhysicalAddressSpace,
+
+    /// Traps set by the VMM
+    traps: TrapMap,
+
+    /// Architecture state, such as the extended page tables
+    arch: SpinMutex<ArchGuest>,
+
+    /// Number of vCPUs created
+    vcpu_count: AtomicUsize,
+}
+
+impl Guest {
+    /// Create a guest with an empty address space
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            id: NEXT_GUEST_ID.fetch_add(1, Ordering::Relaxed),
+            koid: alloc_koid(),
+            gpas: GuestPhysicalAddressSpace::new(GUEST_PHYS_SIZE)?,
+            traps: TrapMap::new(),
+            arch: SpinMutex::new(ArchGuest::new()?),
+            vcpu_count: AtomicUsize::new(0),
+        })
     }
 
-    pub fn alloc(&self) -> Result<T> {
-        let mut next_id = self.next_id.lock();
-        let id = *next_id;
-        *next_id = id + 1;
-        T::try_from(id).map_err(|_| RX_ERR_OUT_OF_RANGE)
+    /// Guest-physical memory
+    pub fn gpas(&self) -> &GuestPhysicalAddressSpace {
+        &self.gpas
     }
 
-    pub fn free(&self, _id: T) -> Result<()> {
+    /// Back guest memory with a VMO range
+    ///
+    /// See [`GuestPhysicalAddressSpace::map`].
+    pub fn map(&self, addr: u64, vmo: Arc<Vmo>, vmo_offset: usize, len: usize, flags: u32) -> Result {
+        self.gpas.map(addr, vmo, vmo_offset, len, flags)
+    }
+
+    /// Remove guest memory mappings
+    ///
+    /// Pages already faulted in are dropped from the extended page tables
+    /// too.
+    /// See [`GuestPhysicalAddressSpace::unmap`].
+    pub fn unmap(&self, addr: u64, len: usize) -> Result {
+        self.gpas.unmap(addr, len)?;
+        self.arch.lock().unmap(addr, len);
         Ok(())
     }
-}
 
-impl<T, const N: usize> Default for IdAllocator<T, N>
-where
-    T: Copy + Clone + PartialEq + TryFrom<u64>,
-{
-    fn default() -> Self {
-        Self::new()
+    /// Add a trap
+    ///
+    /// Memory traps must be page-aligned, since they work by keeping their
+    /// pages out of the extended page tables. `BELL` traps need a port.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_INVALID_ARGS` - unknown kind, bad range, or a port missing
+    ///   from a `BELL` trap
+    /// - `RX_ERR_ALREADY_EXISTS` - the trap overlaps another one
+    pub fn set_trap(&self, trap: Trap) -> Result {
+        match trap.kind {
+            trap_kind::IO => {
+                if trap.len == 0 || trap.addr.checked_add(trap.len as u64).map_or(true, |end| end > IO_PORT_COUNT) {
+                    return Err(RX_ERR_INVALID_ARGS);
+                }
+            }
+            trap_kind::BELL | trap_kind::MMIO | trap_kind::WRITE => {
+                if !page_range_valid(trap.addr, trap.len as u64, self.gpas.size()) {
+                    return Err(RX_ERR_INVALID_ARGS);
+                }
+                if (trap.kind == trap_kind::BELL) != trap.port.is_some() {
+                    return Err(RX_ERR_INVALID_ARGS);
+                }
+            }
+            _ => return Err(RX_ERR_INVALID_ARGS),
+        }
+
+        self.traps.insert(trap)?;
+        if !trap.is_io() {
+            // Pages faulted in before the trap was set must fault again
+            self.arch.lock().unmap(trap.addr, trap.len);
+        }
+        Ok(())
+    }
+
+    /// Resolve a guest memory fault at `addr`
+    ///
+    /// Reads of a `WRITE` trap are served from guest memory, mapped
+    /// read-only so that writes still trap.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_NOT_FOUND` - nothing is mapped or trapped at `addr`
+    /// - `RX_ERR_ACCESS_DENIED` - a write to read-only guest memory
+    pub fn fault(&self, addr: u64, write: bool) -> Result<Fault> {
+        if let Some(trap) = self.traps.find(false, addr) {
+            if trap.kind != trap_kind::WRITE || write {
+                return Ok(Fault::Trap(trap));
+            }
+            let (paddr, _) = self.gpas.fault(addr, false)?;
+            return Ok(Fault::Map { paddr, writable: false });
+        }
+
+        let (paddr, writable) = self.gpas.fault(addr, write)?;
+        Ok(Fault::Map { paddr, writable })
+    }
+
+    /// Find the I/O trap covering `port`
+    pub fn io_trap(&self, port: u16) -> Option<Trap> {
+        self.traps.find(true, port as u64)
+    }
+
+    /// Reserve the next vCPU number
+    fn alloc_vcpu(&self) -> Result<u16> {
+        let index = self.vcpu_count.fetch_add(1, Ordering::AcqRel);
+        if index >= MAX_VCPUS {
+            self.vcpu_count.fetch_sub(1, Ordering::AcqRel);
+            return Err(RX_ERR_NO_RESOURCES);
+        }
+        Ok(index as u16)
     }
 }
 
-/// Port dispatcher for handling port I/O
+/// ============================================================================
+/// vCPUs
+/// ============================================================================
+
+/// vCPU identifier
+pub type VcpuId = u64;
+
+/// Next vCPU ID counter
+static NEXT_VCPU_ID: AtomicU64 = AtomicU64::new(1);
+
+/// VCPU state kinds
+pub mod vcpu_state {
+    /// General registers
+    pub const REGS: u32 = 0;
+
+    /// Index of RIP in `REGS` state
+    pub const X86_RIP: usize = 16;
+
+    /// Index of RFLAGS in `REGS` state
+    pub const X86_RFLAGS: usize = 17;
+
+    /// Index of CR3 in `REGS` state
+    pub const X86_CR3: usize = 18;
+}
+
+/// VCPU state
+///
+/// For `REGS`, `regs[0..16]` are the general registers in instruction
+/// encoding order (RAX, RCX, RDX, RBX, RSP, RBP, RSI, RDI, R8-R15),
+/// followed by RIP, RFLAGS and CR3.
 #[repr(C)]
-pub struct PortDispatcher {
-    port: u16,
+#[derive(Debug, Clone, Copy)]
+pub struct VcpuState {
+    /// Architecture-specific state
+    pub regs: [u64; 64],
 }
 
-impl PortDispatcher {
-    pub fn new(_port: u16) -> Self {
-        Self { port: 0 }
+impl Default for VcpuState {
+    fn default() -> Self {
+        Self { regs: [0; 64] }
     }
 }
 
+/// Why a vCPU left the guest, as decoded by the architecture backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestExit {
+    /// The guest touched guest memory that isn't mapped for the access
+    PageFault { addr: u64, write: bool },
+
+    /// The guest accessed an I/O port
+    Io { port: u16, access_size: u8, input: bool, data: u32 },
+
+    /// A host interrupt arrived while the guest ran
+    Interrupt,
+
+    /// The guest is idle until its next interrupt
+    Wait,
+
+    /// The guest powered off
+    Shutdown,
+
+    /// An exit the backend doesn't handle, with its architecture code
+    Unhandled(u32),
+}
+
+/// A trapped guest memory access, decoded from the guest's instruction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemAccess {
+    /// Access size in bytes, 0 if the instruction couldn't be decoded
+    pub size: u8,
+
+    /// Register read into or written from
+    pub reg: u8,
+
+    /// Value of a write
+    pub data: u64,
+}
+
 /// Interrupt tracker for managing virtual interrupts
 #[repr(C)]
 pub struct InterruptTracker<const N: usize> {
@@ -157,6 +608,24 @@ impl<const N: usize> InterruptTracker<N> {
             Err(RX_ERR_INVALID_ARGS)
         }
     }
+
+    /// Whether any vector is pending
+    pub fn has_pending(&self) -> bool {
+        self.bitmap.lock().iter().any(|&word| word != 0)
+    }
+
+    /// Take the lowest pending vector
+    pub fn pop(&self) -> Option<u32> {
+        let mut bitmap = self.bitmap.lock();
+        for (index, word) in bitmap.iter_mut().enumerate() {
+            if *word != 0 {
+                let bit = word.trailing_zeros();
+                *word &= !(1 << bit);
+                return Some(index as u32 * 64 + bit);
+            }
+        }
+        None
+    }
 }
 
 impl<const N: usize> Default for InterruptTracker<N> {
@@ -165,6 +634,329 @@ impl<const N: usize> Default for InterruptTracker<N> {
     }
 }
 
+/// Number of virtual interrupt vectors a vCPU tracks
+pub const VCPU_VECTORS: u32 = 256;
+
+/// Virtual CPU
+///
+/// A vCPU runs on the thread that calls [`Vcpu::enter`]; other threads can
+/// only [`kick`](Vcpu::kick) it or queue interrupts for it.
+pub struct Vcpu {
+    /// vCPU ID
+    pub id: VcpuId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// Guest the vCPU belongs to
+    guest: Arc<Guest>,
+
+    /// vCPU number within the guest
+    index: u16,
+
+    /// Architecture state; locked for as long as `enter` runs
+    arch: Mutex<ArchVcpu>,
+
+    /// Set by `kick`, cleared when `enter` reports it
+    kicked: AtomicBool,
+
+    /// Thread blocked in `enter` while the guest is halted
+    waiter: Mutex<Option<ThreadId>>,
+
+    /// Virtual interrupts waiting to be delivered
+    interrupts: InterruptTracker<{ (VCPU_VECTORS / 64) as usize }>,
+}
+
+impl Vcpu {
+    /// Create a vCPU for `guest` that starts executing at `entry`
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_NOT_SUPPORTED` - no virtualization support
+    /// - `RX_ERR_NO_RESOURCES` - the guest has `MAX_VCPUS` vCPUs already
+    pub fn new(guest: Arc<Guest>, entry: u64) -> Result<Self> {
+        let index = guest.alloc_vcpu()?;
+        let arch = ArchVcpu::new(&guest.arch.lock(), index, entry)?;
+        Ok(Self {
+            id: NEXT_VCPU_ID.fetch_add(1, Ordering::Relaxed),
+            koid: alloc_koid(),
+            guest,
+            index,
+            arch: Mutex::new(arch),
+            kicked: AtomicBool::new(false),
+            waiter: Mutex::new(None),
+            interrupts: InterruptTracker::new(),
+        })
+    }
+
+    /// vCPU number within the guest
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Run the guest until it needs the VMM
+    ///
+    /// Returns the packet describing why.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_NOT_FOUND` - the guest touched memory or a port with no
+    ///   mapping or trap
+    /// - `RX_ERR_ACCESS_DENIED` - the guest wrote read-only memory
+    /// - `RX_ERR_NOT_SUPPORTED` - an exit the kernel can't handle
+    /// - `RX_ERR_BAD_STATE` - the guest's register state can't be entered
+    pub fn enter(&self) -> Result<PortPacket> {
+        let mut arch = self.arch.lock();
+        loop {
+            if self.kicked.swap(false, Ordering::AcqRel) {
+                return Ok(self.vcpu_packet(guest_vcpu_kind::INTERRUPT));
+            }
+
+            let exit = arch.enter(|| self.interrupts.pop())?;
+            VCPU_EXITS.add(1);
+
+            match exit {
+                GuestExit::PageFault { addr, write } => {
+                    match self.guest.fault(addr, write)? {
+                        Fault::Map { paddr, writable } => {
+                            GUEST_PAGE_FAULTS.add(1);
+                            let page = addr & !(PAGE_SIZE - 1);
+                            self.guest.arch.lock().map_page(page, paddr, writable)?;
+                        }
+                        Fault::Trap(trap) => {
+                            let access = arch.decode_access(self.guest.gpas(), write);
+                            if access.size != 0 {
+                                arch.skip_instruction();
+                            }
+                            if access.size != 0 && trap.kind == trap_kind::BELL {
+                                let packet = Self::packet(trap.key, PacketType::GuestBell, PacketPayload {
+                                    guest_bell: GuestBellData { addr, ..Default::default() },
+                                });
+                                if let Some(port) = trap.port {
+                                    port::queue_packet(port, packet)?;
+                                }
+                                continue;
+                            }
+                            VCPU_PACKETS.add(1);
+                            return Ok(Self::packet(trap.key, PacketType::GuestMem, PacketPayload {
+                                guest_mem: GuestMemData {
+                                    addr,
+                                    data: access.data,
+                                    access_size: access.size,
+                                    write: write as u8,
+                                    reg: access.reg,
+                                    ..Default::default()
+                                },
+                            }));
+                        }
+                    }
+                }
+                GuestExit::Io { port, access_size, input, data } => {
+                    let trap = self.guest.io_trap(port).ok_or(RX_ERR_NOT_FOUND)?;
+                    arch.skip_instruction();
+                    VCPU_PACKETS.add(1);
+                    return Ok(Self::packet(trap.key, PacketType::GuestIo, PacketPayload {
+                        guest_io: GuestIoData {
+                            port,
+                            access_size,
+                            input: input as u8,
+                            data,
+                            ..Default::default()
+                        },
+                    }));
+                }
+                GuestExit::Interrupt => {}
+                GuestExit::Wait => self.wait(),
+                GuestExit::Shutdown => {
+                    VCPU_PACKETS.add(1);
+                    return Ok(self.vcpu_packet(guest_vcpu_kind::EXIT));
+                }
+                GuestExit::Unhandled(code) => {
+                    crate::log_error!("vcpu {}: unhandled exit {:#x}", self.id, code);
+                    return Err(RX_ERR_NOT_SUPPORTED);
+                }
+            }
+        }
+    }
+
+    /// Make `enter` return to the VMM
+    ///
+    /// Takes effect the next time the vCPU leaves the guest, at the latest
+    /// on the next host interrupt or preemption timer expiry.
+    pub fn kick(&self) {
+        self.kicked.store(true, Ordering::Release);
+        self.wake();
+    }
+
+    /// Queue virtual interrupt `vector`
+    ///
+    /// Vectors are delivered lowest first, whenever the guest can take
+    /// external interrupts.
+    pub fn interrupt(&self, vector: u32) -> Result {
+        if vector >= VCPU_VECTORS {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        self.interrupts.track(vector)?;
+        self.wake();
+        Ok(())
+    }
+
+    /// Block until the halted guest has an interrupt or the vCPU is kicked
+    fn wait(&self) {
+        let Some(current) = thread::get_current_thread() else {
+            return;
+        };
+
+        // Checked under the waiter lock, so an interrupt queued after the
+        // check finds the thread blocked and wakes it
+        {
+            let mut waiter = self.waiter.lock();
+            if self.interrupts.has_pending() || self.kicked.load(Ordering::Acquire) {
+                return;
+            }
+            *waiter = Some(current.tid());
+            sched::block_current(BlockReason::Io);
+        }
+        thread::reschedule();
+
+        // Woken by something other than `interrupt` or `kick`
+        self.waiter.lock().take();
+    }
+
+    /// Wake the thread waiting for the halted guest
+    fn wake(&self) {
+        if let Some(tid) = self.waiter.lock().take() {
+            sched::wake(tid);
+        }
+    }
+
+    /// Read register state of `kind`
+    ///
+    /// Fails with `RX_ERR_BAD_STATE` while the vCPU is running.
+    pub fn read_state(&self, kind: u32) -> Result<VcpuState> {
+        let arch = self.arch.try_lock().ok_or(RX_ERR_BAD_STATE)?;
+        arch.read_state(kind)
+    }
+
+    /// Write register state of `kind`
+    ///
+    /// Fails with `RX_ERR_BAD_STATE` while the vCPU is running.
+    pub fn write_state(&self, kind: u32, state: &VcpuState) -> Result {
+        let mut arch = self.arch.try_lock().ok_or(RX_ERR_BAD_STATE)?;
+        arch.write_state(kind, state)
+    }
+
+    fn vcpu_packet(&self, kind: u32) -> PortPacket {
+        Self::packet(self.id, PacketType::GuestVcpu, PacketPayload {
+            guest_vcpu: GuestVcpuData { kind, ..Default::default() },
+        })
+    }
+
+    fn packet(key: u64, packet_type: PacketType, payload: PacketPayload) -> PortPacket {
+        PortPacket { key, packet_type, status: 0, payload }
+    }
+}
+
+/// Backend for architectures without guest support
+///
+/// Guests get no extended page tables, since they can never run.
+#[cfg(not(all(target_arch = "x86_64", feature = "hypervisor")))]
+mod unsupported {
+    use super::{GuestExit, GuestPhysicalAddressSpace, MemAccess, VcpuState};
+    use crate::rustux::types::*;
+    use crate::rustux::types::err::*;
+
+    pub struct ArchGuest;
+
+    impl ArchGuest {
+        pub fn new() -> Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn map_page(&mut self, _addr: u64, _paddr: PAddr, _writable: bool) -> Result {
+            Err(RX_ERR_NOT_SUPPORTED)
+        }
+
+        pub fn unmap(&mut self, _addr: u64, _len: usize) {}
+    }
+
+    pub struct ArchVcpu;
+
+    impl ArchVcpu {
+        pub fn new(_guest: &ArchGuest, _index: u16, _entry: u64) -> Result<Self> {
+            Err(RX_ERR_NOT_SUPPORTED)
+        }
+
+        pub fn enter(&mut self, _next_interrupt: impl FnMut() -> Option<u32>) -> Result<GuestExit> {
+            Err(RX_ERR_NOT_SUPPORTED)
+        }
+
+        pub fn decode_access(&mut self, _gpas: &GuestPhysicalAddressSpace, _write: bool) -> MemAccess {
+            MemAccess::default()
+        }
+
+        pub fn skip_instruction(&mut self) {}
+
+        pub fn read_state(&self, _kind: u32) -> Result<VcpuState> {
+            Err(RX_ERR_NOT_SUPPORTED)
+        }
+
+        pub fn write_state(&mut self, _kind: u32, _state: &VcpuState) -> Result {
+            Err(RX_ERR_NOT_SUPPORTED)
+        }
+    }
+}
+
+/// ============================================================================
+/// Helpers for the arch layers
+/// ============================================================================
+
+/// ID Allocator for managing IDs
+#[repr(C)]
+pub struct IdAllocator<T, const N: usize>
+where
+    T: Copy + Clone + PartialEq,
+{
+    next_id: SpinMutex<u64>,
+    max_id: u64,
+    bitmap: SpinMutex<[u64; N]>,
+    _phantom: core::marker::PhantomData<T>,
+}
+
+impl<T, const N: usize> IdAllocator<T, N>
+where
+    T: Copy + Clone + PartialEq + TryFrom<u64>,
+{
+    pub fn new() -> Self {
+        Self {
+            next_id: SpinMutex::new(1),
+            max_id: (N * 64) as u64,
+            bitmap: SpinMutex::new([0u64; N]),
+            _phantom: core::marker::PhantomData,
+        }
+    }
+
+    pub fn alloc(&self) -> Result<T> {
+        let mut next_id = self.next_id.lock();
+        let id = *next_id;
+        *next_id = id + 1;
+        T::try_from(id).map_err(|_| RX_ERR_OUT_OF_RANGE)
+    }
+
+    pub fn free(&self, _id: T) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> Default for IdAllocator<T, N>
+where
+    T: Copy + Clone + PartialEq + TryFrom<u64>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Interrupt type enumeration
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -195,3 +987,101 @@ impl Default for GuestPtr {
         Self { addr: 0 }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::object::vmo::VmoFlags;
+
+    fn vmo(size: usize) -> Arc<Vmo> {
+        Arc::new(Vmo::create(size, VmoFlags::empty).unwrap())
+    }
+
+    fn trap(kind: u32, addr: u64, len: usize) -> Trap {
+        let port = if kind == trap_kind::BELL { Some(1) } else { None };
+        Trap { kind, addr, len, port, key: 7 }
+    }
+
+    #[test]
+    fn test_gpas_map_validation() {
+        let gpas = GuestPhysicalAddressSpace::new(GUEST_PHYS_SIZE).unwrap();
+        let flags = guest_map_flags::READ | guest_map_flags::WRITE;
+
+        assert_eq!(gpas.map(0x1000, vmo(0x4000), 0, 0x4000, flags), Ok(()));
+        assert_eq!(gpas.map(0x4000, vmo(0x2000), 0, 0x2000, flags), Err(RX_ERR_ALREADY_EXISTS));
+        assert_eq!(gpas.map(0x8000, vmo(0x2000), 0, 0x3000, flags), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(gpas.map(0x8001, vmo(0x2000), 0, 0x1000, flags), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(gpas.map(0x8000, vmo(0x2000), 0, 0x1000, guest_map_flags::WRITE), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(gpas.map(GUEST_PHYS_SIZE, vmo(0x1000), 0, 0x1000, flags), Err(RX_ERR_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn test_gpas_unmap() {
+        let gpas = GuestPhysicalAddressSpace::new(GUEST_PHYS_SIZE).unwrap();
+        gpas.map(0x1000, vmo(0x2000), 0, 0x2000, guest_map_flags::READ).unwrap();
+        gpas.map(0x3000, vmo(0x1000), 0, 0x1000, guest_map_flags::READ).unwrap();
+
+        // Straddling the first mapping
+        assert_eq!(gpas.unmap(0x2000, 0x2000), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(gpas.unmap(0x1000, 0x3000), Ok(()));
+        assert_eq!(gpas.unmap(0x1000, 0x3000), Err(RX_ERR_NOT_FOUND));
+    }
+
+    #[test]
+    fn test_gpas_fault_permissions() {
+        let gpas = GuestPhysicalAddressSpace::new(GUEST_PHYS_SIZE).unwrap();
+        gpas.map(0x1000, vmo(0x1000), 0, 0x1000, guest_map_flags::READ).unwrap();
+
+        assert_eq!(gpas.fault(0x1000, true), Err(RX_ERR_ACCESS_DENIED));
+        assert_eq!(gpas.fault(0x2000, false), Err(RX_ERR_NOT_FOUND));
+    }
+
+    #[test]
+    fn test_trap_map() {
+        let traps = TrapMap::new();
+        assert!(traps.insert(trap(trap_kind::MMIO, 0x1000, 0x2000)).is_ok());
+        assert_eq!(traps.insert(trap(trap_kind::MMIO, 0x2000, 0x1000)).err(), Some(RX_ERR_ALREADY_EXISTS));
+
+        // Ports are a separate space
+        assert!(traps.insert(trap(trap_kind::IO, 0x1000, 8)).is_ok());
+
+        assert_eq!(traps.find(false, 0x2fff).map(|t| t.kind), Some(trap_kind::MMIO));
+        assert!(traps.find(false, 0x3000).is_none());
+        assert_eq!(traps.find(true, 0x1007).map(|t| t.kind), Some(trap_kind::IO));
+        assert!(traps.find(true, 0x1008).is_none());
+    }
+
+    #[test]
+    fn test_guest_set_trap_validation() {
+        let guest = Guest::new().unwrap();
+        assert_eq!(guest.set_trap(trap(trap_kind::MMIO, 0x1001, 0x1000)), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(guest.set_trap(trap(trap_kind::IO, 0xfff8, 0x10)), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(guest.set_trap(Trap { port: None, ..trap(trap_kind::BELL, 0x1000, 0x1000) }), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(guest.set_trap(trap(9, 0x1000, 0x1000)), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(guest.set_trap(trap(trap_kind::MMIO, 0x1000, 0x1000)), Ok(()));
+    }
+
+    #[test]
+    fn test_guest_fault_hits_traps() {
+        let guest = Guest::new().unwrap();
+        guest.map(0x10000, vmo(0x1000), 0, 0x1000, guest_map_flags::READ | guest_map_flags::WRITE).unwrap();
+        guest.set_trap(trap(trap_kind::MMIO, 0x20000, 0x1000)).unwrap();
+        guest.set_trap(trap(trap_kind::WRITE, 0x10000, 0x1000)).unwrap();
+
+        assert!(matches!(guest.fault(0x20010, false), Ok(Fault::Trap(t)) if t.kind == trap_kind::MMIO));
+        assert!(matches!(guest.fault(0x10000, true), Ok(Fault::Trap(t)) if t.kind == trap_kind::WRITE));
+        assert_eq!(guest.fault(0x30000, false).err(), Some(RX_ERR_NOT_FOUND));
+    }
+
+    #[test]
+    fn test_interrupt_tracker_pop() {
+        let tracker: InterruptTracker<4> = InterruptTracker::new();
+        tracker.track(200).unwrap();
+        tracker.track(3).unwrap();
+        assert!(tracker.has_pending());
+        assert_eq!(tracker.pop(), Some(3));
+        assert_eq!(tracker.pop(), Some(200));
+        assert_eq!(tracker.pop(), None);
+        assert!(tracker.track(256).is_err());
+    }
+}
@@ -51,6 +51,7 @@ use crate::kernel::syscalls;
 use crate::kernel::usercopy;
 use crate::kernel::percpu;
 use crate::kernel::cmdline;
+use crate::kernel::handoff;
 use crate::kernel::debug;
 
 // Import logging macros
@@ -162,6 +163,13 @@ fn init_early() {
     // Initialize command line parsing
     cmdline::init();
     log_info!("Command line parsing initialized");
+    debug::log_init_cmdline();
+
+    // Consume the loader handoff (boot arguments, memory map, ACPI)
+    handoff::init();
+
+    // Bring up the framebuffer console as soon as we know about it
+    crate::kernel::dev::fbcon::init_from_handoff();
 
     unsafe {
         INIT_STATE = InitState::Early;
@@ -179,6 +187,18 @@ fn init_arch() {
     // Initialize per-CPU data
     percpu::percpu_init();
 
+    // CPU masks and IPI queues
+    crate::kernel::mp::init();
+
+    // Parse firmware tables (no-op without an RSDP)
+    crate::kernel::dev::acpi::init();
+
+    // Parse the device tree (no-op without a DTB)
+    crate::kernel::dev::fdt::init();
+
+    // NUMA nodes from the SRAT or the device tree
+    crate::kernel::numa::init();
+
     // Architecture-specific initialization would happen here
     // - Interrupt controllers
     // - Timer hardware
@@ -189,6 +209,9 @@ fn init_arch() {
     {
         log_info!("ARM64 architecture initialization");
         // crate::arch::arm64::init();
+
+        // PSCI, GIC, console UART and timer from the device tree
+        crate::kernel::dev::fdt::platform_init();
     }
 
     #[cfg(target_arch = "x86_64")]
@@ -200,9 +223,17 @@ fn init_arch() {
     #[cfg(target_arch = "riscv64")]
     {
         log_info!("RISC-V architecture initialization");
-        // crate::arch::riscv64::init();
+
+        // PLIC and timebase frequency from the device tree
+        crate::kernel::dev::fdt::platform_init();
     }
 
+    // Seed the CPRNG now that boot loader seeds are available
+    crate::kernel::lib::crypto::init();
+
+    // The timer is running, so console lines can show record timestamps
+    debug::log_set_timestamps(true);
+
     unsafe {
         INIT_STATE = InitState::Arch;
     }
@@ -251,6 +282,13 @@ fn init_threads() {
     sched::init();
     log_info!("Scheduler initialized");
 
+    // Bring up secondary CPUs described by the MADT
+    #[cfg(target_arch = "x86_64")]
+    crate::kernel::arch::amd64::smp::x86_init_smp_from_acpi();
+
+    // Idle states and the online mask, before any CPU idles
+    crate::kernel::power::init();
+
     unsafe {
         INIT_STATE = InitState::Scheduler;
     }
@@ -264,10 +302,52 @@ fn init_threads() {
 fn init_late() {
     log_debug!("init_late: starting");
 
+    // Stop for the debugger with kernel.gdb=serial (needs the physmap)
+    crate::kernel::lib::gdbstub::init();
+
+    // Recover the previous boot's crash log (needs the physmap and heap)
+    crate::kernel::lib::crashlog::init();
+
+    // Allocate the trace rings (needs the heap and the CPU count)
+    crate::kernel::lib::ktrace::init();
+
+    // Allocate the profiler's sample rings (needs the heap and the CPU count)
+    crate::kernel::lib::profiler::init();
+
+    // Report the performance counters and instruction trace
+    crate::kernel::lib::perfmon::init();
+    crate::kernel::lib::itrace::init();
+
+    // Enumerate PCI (needs the heap)
+    crate::kernel::dev::pcie::bus::init();
+
     // Initialize syscall layer
     syscalls::init();
     log_info!("Syscall layer initialized");
 
+    // Memory pressure events (needs the event registry and DPCs)
+    vm::pressure::init();
+
+    // Background kernel work (needs the scheduler), then the first batch
+    // of pre-zeroed pages
+    crate::kernel::workqueue::init();
+    crate::kernel::pmm::pmm_prefill_zero_pages();
+
+    // Background leak audits in debug builds (needs the workqueue)
+    crate::kernel::object::leak::init();
+
+    // Shared zero page for read faults on untouched anonymous memory
+    vm::zero_page::init();
+
+    // TPM for measured boot (needs the firmware tables and the heap)
+    crate::kernel::dev::tpm::init();
+
+    // EFI runtime services (needs the VM and the handoff)
+    crate::kernel::lib::efi::init();
+
+    // Hang detection (needs the scheduler, PCI and the firmware tables)
+    crate::kernel::lib::watchdog::init();
+
     // User/kernel boundary safety
     usercopy::init();
     log_info!("User/kernel boundary safety initialized");
@@ -296,13 +376,24 @@ pub fn kernel_running() {
             log_info!("Created idle thread for CPU 0: tid={}", idle_thread.tid());
             idle_thread.start().ok();
             // Register thread in global registry
-            thread::register_thread(alloc::sync::Arc::new(idle_thread));
+            thread::register_thread(idle_thread.into_ref());
         }
         Err(e) => {
             log_error!("Failed to create idle thread: {:?}", e);
         }
     }
 
+    // Run the #[test_case] tests and exit, if enabled with kernel.test=
+    #[cfg(feature = "ktest")]
+    crate::kernel::tests::runner::run_boot_tests();
+
+    // Fuzz the syscall dispatcher and exit, if enabled with kernel.sysfuzz=
+    #[cfg(feature = "ktest")]
+    crate::kernel::tests::syscall_fuzz::run_boot_fuzz();
+
+    // Debug shell on the serial console, if enabled with kernel.shell=true
+    crate::kernel::lib::console::start();
+
     log_info!("Starting scheduler...");
     // The scheduler would now take over and start scheduling threads
 }
@@ -315,13 +406,13 @@ extern "C" fn idle_thread_entry(_cpu_id: usize) -> ! {
     log_info!("Idle thread started for CPU {}", _cpu_id);
 
     loop {
-        // In a real implementation, this would:
-        // 1. Check for pending work
-        // 2. If no work, halt the CPU until interrupt
-        // 3. Repeat
+        // Pre-zero free pages while there is nothing else to do
+        if crate::kernel::pmm::pmm_scrub_idle() {
+            continue;
+        }
 
-        // For now, just spin
-        core::hint::spin_loop();
+        // Sleep until an interrupt, or park if the CPU is going offline
+        crate::kernel::power::idle::cpu_idle();
     }
 }
 
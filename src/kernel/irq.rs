@@ -0,0 +1,233 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Interrupt Entry and Exit
+//!
+//! Architecture-independent bookkeeping around hardware interrupt
+//! handlers. Each architecture's IRQ path brackets its dispatch with
+//! [`enter`] and [`exit`], and the interrupt controller driver calls
+//! [`record_vector`] once it knows which vector fired.
+//!
+//! # Design
+//!
+//! - **Nesting depth**: Each CPU counts how deep in interrupt handlers it
+//!   is (`PerCpu::irq_depth`), so code can ask [`in_irq`] and a handler
+//!   that re-enables interrupts can be interrupted again.
+//! - **Deferred preemption**: Preempting inside a handler would switch
+//!   away with the interrupted frame half unwound. Handlers instead call
+//!   [`request_preempt`] (or the scheduler sets its own pending flag, as
+//!   the timer tick does), and only the outermost [`exit`] reports that
+//!   the thread should be preempted. The arch code then calls
+//!   `thread_preempt()`, directly or via `ARM64_IRQ_EXIT_RESCHEDULE` on
+//!   the way back to user mode, which switches to the thread the
+//!   scheduler picks. Only x86 switches stacks so far; elsewhere the
+//!   interrupted thread keeps running. A thread with preemption disabled
+//!   is left alone until it re-enables it (see [`crate::kernel::preempt`]).
+//! - **Deferred work**: The outermost [`exit`] also runs a bounded number
+//!   of queued DPCs (see [`crate::kernel::dpc`]).
+//! - **Per-vector counters**: Vectors 0-255 each have a kcounter named
+//!   `kernel.irq.vector.0x<nn>`; higher vectors (GIC SPIs past 255) share
+//!   `kernel.irq.vector.other`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! irq::enter();
+//! let vector = ack_interrupt();
+//! irq::record_vector(vector);
+//! dispatch(vector);
+//! if irq::exit() {
+//!     thread::thread_preempt();
+//! }
+//! ```
+
+
+use crate::kernel::dpc;
+use crate::kernel::lib::counters::Kcounter;
+use crate::kernel::percpu::{self, PerCpu, SMP_MAX_CPUS};
+use crate::kernel::preempt;
+use crate::kernel::sched;
+use core::sync::atomic::Ordering;
+
+// Import KCOUNTER macros at crate level
+use crate::{KCOUNTER, KCOUNTER_MAX};
+
+/// ============================================================================
+/// Counters
+/// ============================================================================
+
+KCOUNTER!(IRQ_ENTERED, "kernel.irq.entered");
+KCOUNTER!(IRQ_NESTED, "kernel.irq.nested");
+KCOUNTER!(IRQ_PREEMPTIONS, "kernel.irq.preemptions");
+KCOUNTER_MAX!(IRQ_MAX_DEPTH, "kernel.irq.max_depth");
+KCOUNTER!(IRQ_VECTOR_OTHER, "kernel.irq.vector.other");
+
+/// Number of vectors with their own counter
+pub const NUM_COUNTED_VECTORS: usize = 256;
+
+/// One row of vector counters, `0x<hi>0` to `0x<hi>f`
+macro_rules! vector_counter_row {
+    ($hi:tt; $($lo:tt)*) => {
+        [$({
+            KCOUNTER!(C, concat!("kernel.irq.vector.0x", stringify!($hi), stringify!($lo)));
+            &C
+        }),*]
+    };
+}
+
+/// All vector counters, indexed by high and low hex digit
+macro_rules! vector_counters {
+    ($($hi:tt)*) => {
+        [$(vector_counter_row!($hi; 0 1 2 3 4 5 6 7 8 9 a b c d e f)),*]
+    };
+}
+
+static VECTOR_COUNTERS: [[&Kcounter; 16]; 16] =
+    vector_counters!(0 1 2 3 4 5 6 7 8 9 a b c d e f);
+
+/// Counter for `vector`
+fn vector_counter(vector: u32) -> &'static Kcounter {
+    let vector = vector as usize;
+    if vector < NUM_COUNTED_VECTORS {
+        VECTOR_COUNTERS[vector >> 4][vector & 0xf]
+    } else {
+        &IRQ_VECTOR_OTHER
+    }
+}
+
+/// ============================================================================
+/// Entry and Exit
+/// ============================================================================
+
+/// This CPU's per-CPU data
+fn local() -> &'static PerCpu {
+    let cpu = (percpu::current_cpu_num() as usize).min(SMP_MAX_CPUS - 1);
+    unsafe { percpu::get_percpu(cpu) }
+}
+
+/// Raise `cpu`'s nesting depth, returning the new depth
+fn enter_on(cpu: &PerCpu) -> u32 {
+    cpu.irq_depth.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Lower `cpu`'s nesting depth
+///
+/// Returns whether to preempt: only when leaving the outermost handler
+/// with a preemption requested here or by the scheduler.
+fn exit_on(cpu: &PerCpu, sched_pending: bool) -> bool {
+    let depth = cpu.irq_depth.fetch_sub(1, Ordering::Relaxed);
+    debug_assert!(depth > 0, "interrupt exit without entry");
+    if depth != 1 {
+        return false;
+    }
+    cpu.irq_preempt.swap(false, Ordering::Relaxed) | sched_pending
+}
+
+/// Note entry to a hardware interrupt handler on this CPU
+///
+/// Call with interrupts disabled, before dispatching.
+pub fn enter() {
+    let depth = enter_on(local());
+    IRQ_ENTERED.add(1);
+    if depth > 1 {
+        IRQ_NESTED.add(1);
+    }
+    IRQ_MAX_DEPTH.update_max(depth as i64);
+}
+
+/// Count an interrupt on `vector`
+///
+/// Called by the interrupt controller driver once it has acknowledged the
+/// interrupt, between [`enter`] and [`exit`].
+pub fn record_vector(vector: u32) {
+    vector_counter(vector).add(1);
+}
+
+/// Note exit from a hardware interrupt handler on this CPU
+///
+/// Leaving the outermost handler first runs queued DPCs. Returns true if
+/// this was the outermost handler and the current thread should now be
+/// preempted; the caller must then call `thread_preempt()` once it is safe
+/// to switch, before returning to the interrupted code. A thread with
+/// preemption disabled is instead preempted when it re-enables it.
+pub fn exit() -> bool {
+    let cpu = local();
+
+    // Deferred work runs once, as the outermost handler unwinds
+    if cpu.irq_depth.load(Ordering::Relaxed) == 1 {
+        dpc::dpc_run_on_irq_exit();
+    }
+
+    let preempt = exit_on(cpu, sched::preempt_pending()) && !preempt::defer_on(cpu);
+    if preempt {
+        IRQ_PREEMPTIONS.add(1);
+    }
+    preempt
+}
+
+/// Ask for the current thread to be preempted on the outermost interrupt
+/// exit
+///
+/// Only meaningful in interrupt context; elsewhere, use the scheduler.
+pub fn request_preempt() {
+    local().irq_preempt.store(true, Ordering::Relaxed);
+}
+
+/// Whether this CPU is running an interrupt handler
+pub fn in_irq() -> bool {
+    depth() > 0
+}
+
+/// This CPU's interrupt nesting depth
+pub fn depth() -> u32 {
+    local().irq_depth.load(Ordering::Relaxed)
+}
+
+/// Number of interrupts taken on `vector` across all CPUs
+pub fn vector_count(vector: u32) -> i64 {
+    vector_counter(vector).value()
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting_depth() {
+        let cpu = PerCpu::zeroed();
+        assert_eq!(enter_on(&cpu), 1);
+        assert_eq!(enter_on(&cpu), 2);
+        assert!(!exit_on(&cpu, false));
+        assert!(!exit_on(&cpu, false));
+        assert_eq!(cpu.irq_depth.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_preempt_deferred_to_outermost_exit() {
+        let cpu = PerCpu::zeroed();
+        enter_on(&cpu);
+        enter_on(&cpu);
+        cpu.irq_preempt.store(true, Ordering::Relaxed);
+
+        // The nested handler leaves the request for the outer one
+        assert!(!exit_on(&cpu, true));
+        assert!(cpu.irq_preempt.load(Ordering::Relaxed));
+
+        assert!(exit_on(&cpu, false));
+        assert!(!cpu.irq_preempt.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_scheduler_pending_preempts() {
+        let cpu = PerCpu::zeroed();
+        enter_on(&cpu);
+        assert!(exit_on(&cpu, true));
+    }
+}
@@ -0,0 +1,511 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Address Sanitizer
+//!
+//! Catches out-of-bounds and use-after-free accesses to the kernel heap at
+//! the access that makes them, rather than wherever the corruption is
+//! noticed later.
+//!
+//! # Design
+//!
+//! - **Shadow memory**: One byte per 8-byte granule of the heap. 0 means
+//!   the whole granule is addressable, 1-7 that only that many leading
+//!   bytes are, and a value from 0x80 up that none are, with the value
+//!   saying why (see the `SHADOW_*` constants).
+//! - **Allocator**: Each allocation gets a [`REDZONE`] after it. The
+//!   allocator unpoisons exactly the bytes asked for, and poisons the
+//!   redzone, its own block headers and freed blocks.
+//! - **Quarantine**: Freed blocks wait in a FIFO before going back to the
+//!   allocator, so a use after free is caught even once the memory has
+//!   been asked for again. The backtrace of each free is kept with it.
+//! - **Instrumentation**: The compiler calls the `__asan_*` hooks below on
+//!   every load and store. Addresses outside the heap are not checked.
+//! - **Reports**: A bad access or free panics through
+//!   [`panic_with_report`](crate::kernel::panic::panic_with_report), with
+//!   the bug type, the access, the shadow around it and, for a use after
+//!   free, where it was freed.
+//!
+//! # Building
+//!
+//! Only with the `kasan` feature in debug builds; otherwise [`REDZONE`] is
+//! 0 and every hook is an inline no-op. The instrumentation itself comes
+//! from rustc, calling out to the hooks rather than reading the shadow
+//! inline, and leaving stacks and globals alone since only the heap has
+//! shadow:
+//!
+//! ```text
+//! RUSTFLAGS="-Zsanitizer=kernel-address \
+//!     -Cllvm-args=-asan-instrumentation-with-call-threshold=0 \
+//!     -Cllvm-args=-asan-stack=0 -Cllvm-args=-asan-globals=0" \
+//!     cargo build --features kasan
+//! ```
+
+/// Bytes of redzone after each heap allocation
+pub const REDZONE: usize = if ENABLED { 32 } else { 0 };
+
+/// Whether the heap is sanitized in this build
+pub const ENABLED: bool = cfg!(all(feature = "kasan", debug_assertions));
+
+#[cfg(all(feature = "kasan", debug_assertions))]
+pub use enabled::{init, on_alloc, on_free, Quiet};
+
+/// Start sanitizing the heap at `base`, all of it unallocated
+#[cfg(not(all(feature = "kasan", debug_assertions)))]
+#[inline]
+pub fn init(_base: usize, _size: usize) {}
+
+/// `size` bytes at `ptr` were allocated from a block with `usable` bytes
+#[cfg(not(all(feature = "kasan", debug_assertions)))]
+#[inline]
+pub fn on_alloc(_ptr: *mut u8, _size: usize, _usable: usize) {}
+
+/// `ptr`, from a block with `usable` bytes, is being freed
+///
+/// Returns the block the allocator should free now, or null if it is
+/// being held back.
+#[cfg(not(all(feature = "kasan", debug_assertions)))]
+#[inline]
+pub fn on_free(ptr: *mut u8, _usable: usize) -> *mut u8 {
+    ptr
+}
+
+/// Accesses on this CPU are not checked while this lives
+///
+/// For the allocator, which reads and writes its own poisoned headers.
+#[cfg(not(all(feature = "kasan", debug_assertions)))]
+pub struct Quiet;
+
+#[cfg(not(all(feature = "kasan", debug_assertions)))]
+impl Quiet {
+    /// Stop checking on this CPU
+    #[inline]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// ============================================================================
+/// Sanitizer
+/// ============================================================================
+
+#[cfg(all(feature = "kasan", debug_assertions))]
+mod enabled {
+    use super::REDZONE;
+    use crate::kernel::allocator;
+    use crate::kernel::lib::backtrace::Backtrace;
+    use crate::kernel::panic;
+    use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+    /// log2 of the granule size
+    const SCALE: usize = 3;
+
+    /// Bytes per shadow byte
+    const GRANULE: usize = 1 << SCALE;
+
+    /// Heap never allocated since boot
+    pub const SHADOW_UNALLOCATED: u8 = 0xFE;
+
+    /// Redzone after an allocation
+    pub const SHADOW_REDZONE: u8 = 0xFC;
+
+    /// Freed allocation
+    pub const SHADOW_FREED: u8 = 0xFB;
+
+    /// Allocator block header
+    pub const SHADOW_HEADER: u8 = 0xFA;
+
+    /// Freed blocks held back from the allocator
+    const QUARANTINE_LEN: usize = 256;
+
+    static mut SHADOW: [u8; allocator::HEAP_SIZE >> SCALE] = [0; allocator::HEAP_SIZE >> SCALE];
+
+    /// Heap covered by the shadow; empty until [`init`]
+    static HEAP_BASE: AtomicUsize = AtomicUsize::new(0);
+    static HEAP_END: AtomicUsize = AtomicUsize::new(0);
+
+    /// Per-CPU [`Quiet`] depth
+    static QUIET: [AtomicU32; SMP_MAX_CPUS] = [const { AtomicU32::new(0) }; SMP_MAX_CPUS];
+
+    /// ========================================================================
+    /// Shadow Encoding
+    /// ========================================================================
+
+    /// Mark `len` bytes at granule-aligned `offset` addressable
+    fn unpoison(shadow: &mut [u8], offset: usize, len: usize) {
+        let first = offset >> SCALE;
+        let full = len >> SCALE;
+        shadow[first..first + full].fill(0);
+        if len % GRANULE != 0 {
+            shadow[first + full] = (len % GRANULE) as u8;
+        }
+    }
+
+    /// Mark the granules overlapping `[offset, offset + len)` with `value`
+    ///
+    /// A granule partly before `offset` keeps its addressable prefix.
+    fn poison(shadow: &mut [u8], offset: usize, len: usize, value: u8) {
+        if len == 0 {
+            return;
+        }
+        let mut first = offset >> SCALE;
+        if offset % GRANULE != 0 {
+            shadow[first] = (offset % GRANULE) as u8;
+            first += 1;
+        }
+        let last = (offset + len + GRANULE - 1) >> SCALE;
+        if first < last {
+            shadow[first..last].fill(value);
+        }
+    }
+
+    /// First byte of `[offset, offset + size)` that isn't addressable
+    fn first_bad(shadow: &[u8], offset: usize, size: usize) -> Option<usize> {
+        let end = offset + size;
+        let mut at = offset;
+        while at < end {
+            let value = shadow[at >> SCALE];
+            let granule_end = (at | (GRANULE - 1)) + 1;
+            if value != 0 {
+                if value >= 0x80 || at % GRANULE >= value as usize {
+                    return Some(at);
+                }
+                // Addressable up to `value` within this granule
+                let limit = (at & !(GRANULE - 1)) + value as usize;
+                if end > limit {
+                    return Some(limit);
+                }
+            }
+            at = granule_end;
+        }
+        None
+    }
+
+    fn bug_type(value: u8) -> &'static str {
+        match value {
+            SHADOW_FREED => "use-after-free",
+            SHADOW_HEADER => "heap-header-access",
+            SHADOW_UNALLOCATED => "wild-heap-access",
+            _ => "heap-buffer-overflow",
+        }
+    }
+
+    /// ========================================================================
+    /// Allocator Hooks
+    /// ========================================================================
+
+    fn shadow() -> &'static mut [u8] {
+        unsafe { &mut *core::ptr::addr_of_mut!(SHADOW) }
+    }
+
+    fn heap_offset(addr: usize) -> usize {
+        addr - HEAP_BASE.load(Ordering::Relaxed)
+    }
+
+    fn this_cpu() -> usize {
+        if percpu::num_cpus() == 0 {
+            0
+        } else {
+            (percpu::current_cpu_num() as usize).min(SMP_MAX_CPUS - 1)
+        }
+    }
+
+    /// Accesses on this CPU are not checked while this lives
+    ///
+    /// For the allocator, which reads and writes its own poisoned headers.
+    pub struct Quiet {
+        cpu: usize,
+    }
+
+    impl Quiet {
+        /// Stop checking on this CPU
+        pub fn new() -> Self {
+            let cpu = this_cpu();
+            QUIET[cpu].fetch_add(1, Ordering::Relaxed);
+            Self { cpu }
+        }
+    }
+
+    impl Drop for Quiet {
+        fn drop(&mut self) {
+            QUIET[self.cpu].fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Start sanitizing the heap at `base`, all of it unallocated
+    pub fn init(base: usize, size: usize) {
+        let size = size.min(allocator::HEAP_SIZE);
+        shadow()[..size >> SCALE].fill(SHADOW_UNALLOCATED);
+        HEAP_BASE.store(base, Ordering::Relaxed);
+        HEAP_END.store(base + size, Ordering::Release);
+    }
+
+    /// `size` bytes at `ptr` were allocated from a block with `usable` bytes
+    pub fn on_alloc(ptr: *mut u8, size: usize, usable: usize) {
+        let offset = heap_offset(ptr as usize);
+        let shadow = shadow();
+        poison(shadow, offset - allocator::HEADER_SIZE, allocator::HEADER_SIZE, SHADOW_HEADER);
+        unpoison(shadow, offset, size);
+        poison(shadow, offset + size, usable - size, SHADOW_REDZONE);
+    }
+
+    struct Quarantined {
+        ptr: usize,
+        usable: usize,
+        freed_by: Backtrace,
+    }
+
+    struct Quarantine {
+        blocks: [Quarantined; QUARANTINE_LEN],
+        next: usize,
+    }
+
+    static QUARANTINE: spin::Mutex<Quarantine> = spin::Mutex::new(Quarantine {
+        blocks: [const { Quarantined { ptr: 0, usable: 0, freed_by: Backtrace::new() } }; QUARANTINE_LEN],
+        next: 0,
+    });
+
+    /// `ptr`, from a block with `usable` bytes, is being freed
+    ///
+    /// Returns the block the allocator should free now, or null if it is
+    /// being held back.
+    pub fn on_free(ptr: *mut u8, usable: usize) -> *mut u8 {
+        let addr = ptr as usize;
+        if addr < HEAP_BASE.load(Ordering::Relaxed) || addr >= HEAP_END.load(Ordering::Acquire) {
+            return ptr;
+        }
+        let offset = heap_offset(addr);
+        let shadow = shadow();
+
+        let value = shadow[offset >> SCALE];
+        let header = shadow[(offset >> SCALE).wrapping_sub(1)];
+        if value == SHADOW_FREED {
+            report_free("double-free", addr);
+        }
+        if addr % GRANULE != 0 || value >= 0x80 || header != SHADOW_HEADER {
+            report_free("invalid-free", addr);
+        }
+
+        poison(shadow, offset, usable, SHADOW_FREED);
+
+        let mut quarantine = QUARANTINE.lock();
+        let slot = quarantine.next;
+        quarantine.next = (slot + 1) % QUARANTINE_LEN;
+        let evicted = core::mem::replace(
+            &mut quarantine.blocks[slot],
+            Quarantined { ptr: addr, usable, freed_by: Backtrace::capture() },
+        );
+        evicted.ptr as *mut u8
+    }
+
+    /// ========================================================================
+    /// Access Checks
+    /// ========================================================================
+
+    #[inline]
+    fn check(addr: usize, size: usize, write: bool) {
+        if size == 0 || addr >= HEAP_END.load(Ordering::Acquire) {
+            return;
+        }
+        let base = HEAP_BASE.load(Ordering::Relaxed);
+        if addr + size <= base || QUIET[this_cpu()].load(Ordering::Relaxed) != 0 {
+            return;
+        }
+        let start = addr.max(base);
+        let end = (addr + size).min(HEAP_END.load(Ordering::Relaxed));
+        if let Some(bad) = first_bad(shadow(), start - base, end - start) {
+            report_access(addr, size, write, base + bad);
+        }
+    }
+
+    /// Print the shadow rows around heap address `bad`
+    fn write_shadow(w: &mut dyn Write, bad: usize) -> core::fmt::Result {
+        const ROW: usize = 16;
+        let base = HEAP_BASE.load(Ordering::Relaxed);
+        let shadow = shadow();
+        let granule = (bad - base) >> SCALE;
+        let row = granule / ROW;
+        let rows = row.saturating_sub(2)..(row + 3).min(shadow.len() / ROW);
+
+        writeln!(w, "Shadow around the bad address (one byte per {} heap bytes):", GRANULE)?;
+        for r in rows {
+            write!(w, "{} {:#018x}:", if r == row { "=>" } else { "  " }, base + r * ROW * GRANULE)?;
+            for i in r * ROW..(r + 1) * ROW {
+                if i == granule {
+                    write!(w, "[{:02x}]", shadow[i])?;
+                } else {
+                    write!(w, " {:02x} ", shadow[i])?;
+                }
+            }
+            writeln!(w)?;
+        }
+        writeln!(
+            w,
+            "Legend: 00 addressable, 01-07 partly addressable, {:02x} redzone, {:02x} freed, {:02x} allocator header, {:02x} never allocated",
+            SHADOW_REDZONE, SHADOW_FREED, SHADOW_HEADER, SHADOW_UNALLOCATED
+        )
+    }
+
+    /// Where the quarantined block holding `addr` was freed
+    fn freed_by(addr: usize) -> Option<Backtrace> {
+        let quarantine = QUARANTINE.try_lock()?;
+        quarantine
+            .blocks
+            .iter()
+            .find(|b| b.ptr != 0 && addr >= b.ptr && addr < b.ptr + b.usable)
+            .map(|b| b.freed_by)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn report_access(addr: usize, size: usize, write: bool, bad: usize) -> ! {
+        let _quiet = Quiet::new();
+        let value = shadow()[heap_offset(bad) >> SCALE];
+        let freed_by = if value == SHADOW_FREED { freed_by(bad) } else { None };
+        panic::panic_with_report(
+            format_args!("KASAN: {} on address {:#x}", bug_type(value), bad),
+            &|w| {
+                writeln!(
+                    w,
+                    "{} of size {} at {:#x} (heap offset {:#x})",
+                    if write { "Write" } else { "Read" },
+                    size,
+                    addr,
+                    heap_offset(bad)
+                )?;
+                if let Some(backtrace) = &freed_by {
+                    writeln!(w, "\nFreed by:")?;
+                    backtrace.write_to(w)?;
+                }
+                writeln!(w)?;
+                write_shadow(w, bad)
+            },
+        )
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn report_free(kind: &str, addr: usize) -> ! {
+        let _quiet = Quiet::new();
+        let freed_by = freed_by(addr);
+        panic::panic_with_report(format_args!("KASAN: {} of {:#x}", kind, addr), &|w| {
+            if let Some(backtrace) = &freed_by {
+                writeln!(w, "Already freed by:")?;
+                backtrace.write_to(w)?;
+                writeln!(w)?;
+            }
+            write_shadow(w, addr)
+        })
+    }
+
+    /// ========================================================================
+    /// Compiler Hooks
+    /// ========================================================================
+
+    macro_rules! asan_hooks {
+        ($($size:literal => $load:ident, $store:ident;)*) => {
+            $(
+                #[no_mangle]
+                pub extern "C" fn $load(addr: usize) {
+                    check(addr, $size, false);
+                }
+
+                #[no_mangle]
+                pub extern "C" fn $store(addr: usize) {
+                    check(addr, $size, true);
+                }
+            )*
+        };
+    }
+
+    asan_hooks! {
+        1 => __asan_load1_noabort, __asan_store1_noabort;
+        2 => __asan_load2_noabort, __asan_store2_noabort;
+        4 => __asan_load4_noabort, __asan_store4_noabort;
+        8 => __asan_load8_noabort, __asan_store8_noabort;
+        16 => __asan_load16_noabort, __asan_store16_noabort;
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __asan_loadN_noabort(addr: usize, size: usize) {
+        check(addr, size, false);
+    }
+
+    #[no_mangle]
+    pub extern "C" fn __asan_storeN_noabort(addr: usize, size: usize) {
+        check(addr, size, true);
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn __asan_memcpy(dst: *mut u8, src: *const u8, len: usize) -> *mut u8 {
+        check(src as usize, len, false);
+        check(dst as usize, len, true);
+        core::ptr::copy_nonoverlapping(src, dst, len);
+        dst
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn __asan_memmove(dst: *mut u8, src: *const u8, len: usize) -> *mut u8 {
+        check(src as usize, len, false);
+        check(dst as usize, len, true);
+        core::ptr::copy(src, dst, len);
+        dst
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn __asan_memset(dst: *mut u8, value: i32, len: usize) -> *mut u8 {
+        check(dst as usize, len, true);
+        core::ptr::write_bytes(dst, value as u8, len);
+        dst
+    }
+
+    /// Only heap memory has shadow, so there are no stack redzones to
+    /// clean up before a function that doesn't return
+    #[no_mangle]
+    pub extern "C" fn __asan_handle_no_return() {}
+
+    /// Globals have no shadow
+    #[no_mangle]
+    pub extern "C" fn __asan_register_globals(_globals: usize, _count: usize) {}
+
+    #[no_mangle]
+    pub extern "C" fn __asan_unregister_globals(_globals: usize, _count: usize) {}
+
+    /// ========================================================================
+    /// Tests
+    /// ========================================================================
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_partial_granule() {
+            let mut shadow = [SHADOW_UNALLOCATED; 8];
+            unpoison(&mut shadow, 8, 13);
+            poison(&mut shadow, 8 + 13, 32 - 13, SHADOW_REDZONE);
+            assert_eq!(shadow[..5], [SHADOW_UNALLOCATED, 0, 5, SHADOW_REDZONE, SHADOW_REDZONE]);
+
+            assert_eq!(first_bad(&shadow, 8, 13), None);
+            assert_eq!(first_bad(&shadow, 16, 8), Some(21));
+            assert_eq!(first_bad(&shadow, 20, 1), None);
+            assert_eq!(first_bad(&shadow, 21, 1), Some(21));
+            assert_eq!(first_bad(&shadow, 4, 8), Some(4));
+        }
+
+        #[test]
+        fn test_bug_types() {
+            let mut shadow = [0u8; 8];
+            poison(&mut shadow, 16, 16, SHADOW_FREED);
+            let bad = first_bad(&shadow, 8, 16).unwrap();
+            assert_eq!(bad, 16);
+            assert_eq!(bug_type(shadow[bad >> SCALE]), "use-after-free");
+            assert_eq!(bug_type(3), "heap-buffer-overflow");
+        }
+    }
+}
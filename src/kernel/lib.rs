@@ -17,21 +17,8 @@
 
 use core::fmt;
 
-/// Console module
-pub mod console {
-    /// Write a single character to the console
-    pub fn putchar(c: u8) {
-        // Placeholder - would write to serial console
-        let _ = c;
-    }
-
-    /// Write a string to the console
-    pub fn puts(s: &str) {
-        for byte in s.bytes() {
-            putchar(byte);
-        }
-    }
-}
+/// Kernel debug shell and command registry
+pub mod console;
 
 /// String module
 pub mod string {
@@ -111,46 +98,18 @@ pub mod mem {
     }
 }
 
-/// Crash logging module
-pub mod crashlog {
-    /// Crash log structure
-    #[repr(C)]
-    pub struct CrashLog {
-        /// Exception frame pointer
-        pub iframe: *mut u8,
-    }
-
-    // SAFETY: CrashLog is safe to share between threads for reading
-    // The iframe pointer is only written during crash handling which is
-    // mutually exclusive with normal operation
-    unsafe impl Sync for CrashLog {}
-
-    /// Global crash log instance
-    pub static crashlog: CrashLog = CrashLog {
-        iframe: core::ptr::null_mut(),
-    };
-
-    /// Log a crash
-    pub fn log_crash(reason: &str) {
-        let _ = reason;
-        // Placeholder - would log to persistent storage
-    }
-}
-
 // ============================================================================
 // Counter Macros (defined at module level for proper re-export)
 // ============================================================================
 
 /// Declare a kernel counter
 ///
-/// This macro creates a new counter with a unique identifier and description.
+/// The counter sums its per-CPU values.
 /// Usage: `KCOUNTER!(COUNTER_NAME, "counter.description");`
 #[macro_export]
 macro_rules! KCOUNTER {
     ($name:ident, $desc:expr) => {
-        #[allow(non_upper_case_globals)]
-        pub static $name: core::sync::atomic::AtomicUsize =
-            core::sync::atomic::AtomicUsize::new(0);
+        $crate::__KCOUNTER_DECLARE!($name, $desc, Sum);
     };
 }
 
@@ -158,25 +117,40 @@ macro_rules! KCOUNTER {
 #[macro_export]
 macro_rules! KCOUNTER_MAX {
     ($name:ident, $desc:expr) => {
+        $crate::__KCOUNTER_DECLARE!($name, $desc, Max);
+    };
+}
+
+/// Place a counter's descriptor and arena slots in their linker sections
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __KCOUNTER_DECLARE {
+    ($name:ident, $desc:expr, $kind:ident) => {
         #[allow(non_upper_case_globals)]
-        pub static $name: core::sync::atomic::AtomicUsize =
-            core::sync::atomic::AtomicUsize::new(0);
+        pub static $name: $crate::kernel::lib::counters::Kcounter = {
+            #[used]
+            #[link_section = concat!("kcountdesc.", $desc)]
+            static DESC: $crate::kernel::lib::counters::KcounterDesc =
+                $crate::kernel::lib::counters::KcounterDesc::new(
+                    concat!($desc, "\0"),
+                    $crate::kernel::lib::counters::KcounterType::$kind,
+                );
+
+            #[used]
+            #[link_section = concat!(".bss.kcounter.", $desc)]
+            static SLOTS: [core::sync::atomic::AtomicI64; $crate::kernel::percpu::SMP_MAX_CPUS] =
+                [const { core::sync::atomic::AtomicI64::new(0) }; $crate::kernel::percpu::SMP_MAX_CPUS];
+
+            $crate::kernel::lib::counters::Kcounter::new(&DESC)
+        };
     };
 }
 
-/// Performance counters module
-pub mod counters {
-    /// Increment a counter
-    pub fn increment(_counter: usize) {
-        // Placeholder - would increment performance counter
-    }
+/// Per-CPU kernel counters
+pub mod counters;
 
-    /// Read a counter
-    pub fn read(_counter: usize) -> usize {
-        // Placeholder
-        0
-    }
-}
+/// Object caches for fixed-size kernel objects
+pub mod slab;
 
 /// Thread lock module placeholder
 pub mod thread_lock {
@@ -215,39 +189,53 @@ pub mod heap {
     }
 }
 
-/// Kernel trace module
-pub mod ktrace {
-    /// Kernel trace tag
-    pub type ktrace_tag_t = u32;
+/// Kernel log ring shared with debuglog objects
+pub mod debuglog;
 
-    /// Initialize kernel tracing
-    pub fn init() {
-        // TODO: Initialize kernel tracing
-    }
+/// Per-CPU binary kernel trace
+pub mod ktrace;
 
-    /// Write a kernel trace entry
-    pub fn write_trace(_tag: ktrace_tag_t, _args: core::fmt::Arguments) {
-        // TODO: Implement trace writing
-    }
+/// Sampling profiler
+pub mod profiler;
 
-    /// Kernel trace probe with 64-bit argument
-    #[inline]
-    pub fn ktrace_probe64(_tag: u32, _arg: u64) {
-        // Stub - no-op for now
-    }
+/// Performance counters
+pub mod perfmon;
 
-    /// Kernel trace probe with no arguments
-    #[inline]
-    pub fn ktrace_probe0(_tag: u32) {
-        // Stub - no-op for now
-    }
+/// Hardware instruction trace
+pub mod itrace;
 
-    /// Kernel trace probe with two arguments
-    #[inline]
-    pub fn ktrace_probe2(_tag: u32, _arg1: u64, _arg2: u64) {
-        // Stub - no-op for now
-    }
-}
+/// GDB remote serial protocol stub
+pub mod gdbstub;
+
+/// Kernel CPRNG and the primitives behind it
+pub mod crypto;
+
+/// Stack canary guard values and `__stack_chk_fail`
+pub mod ssp;
+
+/// Frame pointer backtraces
+pub mod backtrace;
+
+/// Panic output kept across warm reboot
+pub mod crashlog;
+
+/// Software and hardware watchdog
+pub mod watchdog;
+
+/// Time values shared read-only with the vDSO
+pub mod timepage;
+
+/// EFI runtime services: variables and the real-time clock
+pub mod efi;
+
+/// Lock order validation (`lockdep` feature, debug builds)
+pub mod lockdep;
+
+/// Address to symbol lookup using the embedded symbol table
+pub mod symbolize;
+
+/// Kernel version and build ID
+pub mod version;
 
 /// Internal (rustux_internal) module for device-specific functionality
 pub mod rx_internal {
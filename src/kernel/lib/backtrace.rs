@@ -0,0 +1,240 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Frame Pointer Backtraces
+//!
+//! Walks the chain of saved frame pointers to recover return addresses.
+//! The kernel must be built with `-C force-frame-pointers=yes` for the
+//! chain to be complete.
+//!
+//! Frame records by architecture:
+//!
+//! - **x86_64**: `rbp` points at `{ saved rbp, return address }`
+//! - **arm64**: `x29` points at `{ saved x29, saved lr }`
+//! - **riscv64**: `s0` points just above `{ saved s0, saved ra }`
+//!
+//! The walk only ever moves up the stack and stops at the first frame
+//! pointer that is misaligned, outside kernel space, or not above the
+//! previous one, so a corrupt stack ends the trace rather than faulting.
+
+use crate::kernel::arch::KERNEL_BASE;
+use crate::kernel::lib::symbolize;
+use crate::kernel::debug::LogWriter;
+use core::fmt::Write;
+
+/// Maximum number of frames recorded
+pub const MAX_FRAMES: usize = 32;
+
+/// Largest plausible distance between two consecutive frames
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Offsets of the saved frame pointer and return address from the frame pointer
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const FRAME_FP_OFFSET: isize = 0;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const FRAME_RA_OFFSET: isize = 8;
+
+#[cfg(target_arch = "riscv64")]
+const FRAME_FP_OFFSET: isize = -16;
+#[cfg(target_arch = "riscv64")]
+const FRAME_RA_OFFSET: isize = -8;
+
+/// A captured backtrace
+#[derive(Clone, Copy)]
+pub struct Backtrace {
+    pcs: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// An empty backtrace
+    pub const fn new() -> Self {
+        Self {
+            pcs: [0; MAX_FRAMES],
+            len: 0,
+        }
+    }
+
+    /// Capture the caller's backtrace
+    #[inline(never)]
+    pub fn capture() -> Self {
+        let fp = current_frame_pointer();
+        // Skip this function's own frame
+        match unsafe { next_frame(fp) } {
+            Some((caller_fp, _)) => unsafe { Self::from_frame(caller_fp) },
+            None => Self::new(),
+        }
+    }
+
+    /// Walk the frame chain starting at `fp`
+    ///
+    /// # Safety
+    ///
+    /// `fp` must be a frame pointer on a mapped kernel stack, or a value
+    /// rejected by the sanity checks.
+    pub unsafe fn from_frame(fp: usize) -> Self {
+        let mut bt = Self::new();
+        let mut fp = fp;
+
+        while bt.len < MAX_FRAMES {
+            let (next_fp, pc) = match next_frame(fp) {
+                Some(frame) => frame,
+                None => break,
+            };
+            if pc == 0 {
+                break;
+            }
+
+            bt.pcs[bt.len] = pc;
+            bt.len += 1;
+
+            if next_fp <= fp || next_fp - fp > MAX_FRAME_SIZE {
+                break;
+            }
+            fp = next_fp;
+        }
+
+        bt
+    }
+
+    /// Walk the frame chain of an interrupted context
+    ///
+    /// `pc` is recorded as the first frame.
+    ///
+    /// # Safety
+    ///
+    /// Same requirements as [`Backtrace::from_frame`].
+    pub unsafe fn from_context(pc: usize, fp: usize) -> Self {
+        let rest = Self::from_frame(fp);
+        let mut bt = Self::new();
+        bt.push(pc);
+        for &frame in rest.frames() {
+            bt.push(frame);
+        }
+        bt
+    }
+
+    /// Append a frame, dropping it if the trace is full
+    pub fn push(&mut self, pc: usize) {
+        if self.len < MAX_FRAMES {
+            self.pcs[self.len] = pc;
+            self.len += 1;
+        }
+    }
+
+    /// Recorded return addresses, innermost first
+    pub fn frames(&self) -> &[usize] {
+        &self.pcs[..self.len]
+    }
+
+    /// Number of recorded frames
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no frames were recorded
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Print the backtrace, symbolized where possible
+    pub fn print(&self) {
+        let _ = self.write_to(&mut LogWriter);
+    }
+
+    /// Write the backtrace to `w`, one frame per line
+    pub fn write_to(&self, w: &mut dyn Write) -> core::fmt::Result {
+        for (i, &pc) in self.frames().iter().enumerate() {
+            match symbolize::symbolize(pc) {
+                Some(sym) => writeln!(w, "  #{:<2} {:#018x} {}+{:#x}", i, pc, sym.name, sym.offset)?,
+                None => writeln!(w, "  #{:<2} {:#018x}", i, pc)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Backtrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Print the caller's backtrace
+#[inline(never)]
+pub fn print_backtrace() {
+    Backtrace::capture().print();
+}
+
+/// Check that `fp` could be a kernel frame pointer
+fn is_valid_frame_pointer(fp: usize) -> bool {
+    fp != 0
+        && fp % core::mem::size_of::<usize>() == 0
+        && fp as u64 >= KERNEL_BASE
+        && fp.checked_add(16).is_some()
+}
+
+/// Read the frame record at `fp`
+///
+/// Returns the caller's frame pointer and the return address.
+unsafe fn next_frame(fp: usize) -> Option<(usize, usize)> {
+    if !is_valid_frame_pointer(fp) {
+        return None;
+    }
+
+    let base = fp as *const u8;
+    let next_fp = *(base.offset(FRAME_FP_OFFSET) as *const usize);
+    let pc = *(base.offset(FRAME_RA_OFFSET) as *const usize);
+    Some((next_fp, pc))
+}
+
+/// Read the current frame pointer
+#[inline(always)]
+pub fn current_frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        #[cfg(target_arch = "x86_64")]
+        core::arch::asm!("mov {}, rbp", out(reg) fp, options(nomem, nostack));
+
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("mov {}, x29", out(reg) fp, options(nomem, nostack));
+
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("mv {}, s0", out(reg) fp, options(nomem, nostack));
+    }
+    fp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_bad_frame_pointers() {
+        assert!(!is_valid_frame_pointer(0));
+        assert!(!is_valid_frame_pointer(0x1000));
+        assert!(!is_valid_frame_pointer(KERNEL_BASE as usize + 3));
+        assert!(is_valid_frame_pointer(KERNEL_BASE as usize + 0x1000));
+
+        let bt = unsafe { Backtrace::from_frame(0x1000) };
+        assert!(bt.is_empty());
+    }
+
+    #[test]
+    fn test_from_context_records_pc() {
+        let bt = unsafe { Backtrace::from_context(0xffff_ffff_8010_0000, 0) };
+        assert_eq!(bt.frames(), &[0xffff_ffff_8010_0000]);
+    }
+
+    #[test]
+    fn test_push_limit() {
+        let mut bt = Backtrace::new();
+        for i in 0..MAX_FRAMES + 4 {
+            bt.push(i);
+        }
+        assert_eq!(bt.len(), MAX_FRAMES);
+    }
+}
@@ -4,415 +4,470 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT
 
-//! Console
+//! Kernel Debug Shell
 //!
-//! This module provides a command-line console interface for kernel debugging.
-
-#![no_std]
+//! An interactive command line on the serial console, for poking at a
+//! running kernel: `threads`, `ps`, `vm`, `heap`, `counters`, `reboot`
+//! and whatever else subsystems register.
+//!
+//! # Registering Commands
+//!
+//! Any module registers a command with
+//! [`static_command!`](crate::static_command):
+//!
+//! ```ignore
+//! fn cmd_lspci(argc: i32, argv: &[&str], flags: u32) -> i32 { ... }
+//!
+//! crate::static_command!("lspci", "list PCI devices", cmd_lspci);
+//! ```
+//!
+//! The macro places a [`Cmd`] in a `kcmd.<name>` section. The linker script
+//! collects these, sorted by name, between `kcmd_begin` and `kcmd_end`, so
+//! the command table needs no registration at boot and lookup is a binary
+//! search.
+//!
+//! # Command Line
+//!
+//! - `kernel.shell=true` - Start the shell once the kernel is running
+//!   (default off)
 
-extern crate alloc;
+use core::fmt::Write;
 
-use alloc::collections::BTreeMap;
-use alloc::format;
-use alloc::string::{String, ToString};
-use alloc::vec::Vec;
-use core::sync::atomic::{AtomicBool, Ordering};
-use spin::Mutex;
+use crate::kernel::cmdline;
+use crate::kernel::debug::LogWriter;
+use crate::kernel::thread;
 
-use crate::rustux::types::*;
+use crate::{log_error, log_info, print, println};
 
-/// Maximum line length for console commands
+/// Longest command line
 pub const LINE_LEN: usize = 128;
 
-/// Maximum line length for panic messages
-pub const PANIC_LINE_LEN: usize = 32;
-
-/// Maximum number of command arguments
+/// Most arguments to a command, including its name
 pub const MAX_NUM_ARGS: usize = 16;
 
-/// Command history length
+/// Lines kept for recall with the arrow keys
 const HISTORY_LEN: usize = 16;
 
-/// Enable command history
-const CONSOLE_ENABLE_HISTORY: bool = true;
+/// Prompt printed before each line
+const PROMPT: &str = "] ";
 
-/// Whitespace characters for command parsing
-const WHITESPACE: &[char] = &[' ', '\t'];
+/// Command line switch for the shell
+const CMDLINE_SHELL: &str = "kernel.shell";
 
-/// Command availability flags
-pub const CMD_AVAIL_ALWAYS: u32 = 0x1;
-pub const CMD_AVAIL_NORMAL: u32 = 0x2;
-pub const CMD_AVAIL_PANIC: u32 = 0x4;
+/// Command is available from the shell
+pub const CMD_AVAIL_NORMAL: u32 = 0x1;
 
-/// Command function type
-pub type CmdFunc = fn(argc: usize, argv: &[CmdArg], flags: u32) -> i32;
+/// Command is safe to run with interrupts off after a panic
+pub const CMD_AVAIL_PANIC: u32 = 0x2;
 
-/// Command argument
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct CmdArg {
-    /// String argument
-    pub str: &'static str,
-    /// Unsigned integer argument
-    pub u: u64,
-    /// Signed integer argument
-    pub i: i64,
-    /// Float argument
-    pub f: f64,
-    /// Boolean argument
-    pub b: bool,
-}
+/// Command handler
+///
+/// `argv[0]` is the command name. `flags` is the context the command runs
+/// in, one of the `CMD_AVAIL_*` values. Returns 0 on success.
+pub type CmdFunc = fn(argc: i32, argv: &[&str], flags: u32) -> i32;
 
-/// Command descriptor
+/// Command descriptor, one per command in the command section
 #[repr(C)]
-#[derive(Debug, Clone)]
 pub struct Cmd {
     /// Command name
     pub name: &'static str,
-    /// Help string
+
+    /// One-line description for `help`
     pub help: &'static str,
-    /// Command function
-    pub func: Option<CmdFunc>,
-    /// Availability flags
-    pub flags: u32,
-}
 
-/// Console state
-struct ConsoleState {
-    /// Debug buffer
-    debug_buffer: Option<Vec<u8>>,
-    /// Echo commands
-    echo: bool,
-    /// Last command result
-    last_result: i32,
-    /// Abort script flag
-    abort_script: bool,
-    /// Command history
-    #[cfg(CONSOLE_ENABLE_HISTORY)]
-    history: Vec<String>,
-    /// Next history index
-    #[cfg(CONSOLE_ENABLE_HISTORY)]
-    history_next: usize,
+    /// Handler
+    pub func: CmdFunc,
+
+    /// Contexts the command is available in, `CMD_AVAIL_*`
+    pub flags: u32,
 }
 
-impl ConsoleState {
-    fn new() -> Self {
-        Self {
-            debug_buffer: None,
-            echo: true,
-            last_result: 0,
-            abort_script: false,
-            #[cfg(CONSOLE_ENABLE_HISTORY)]
-            history: Vec::with_capacity(HISTORY_LEN),
-            #[cfg(CONSOLE_ENABLE_HISTORY)]
-            history_next: 0,
-        }
+impl Cmd {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, help: &'static str, func: CmdFunc, flags: u32) -> Self {
+        Self { name, help, func, flags }
     }
 }
 
-/// Global console state
-static CONSOLE_STATE: Mutex<ConsoleState> = Mutex::new(ConsoleState::new());
-
-/// Registered commands
-static COMMANDS: Mutex<BTreeMap<&'static str, Cmd>> = Mutex::new(BTreeMap::new());
-
-/// Register a command
-pub fn register_command(cmd: Cmd) {
-    COMMANDS.lock().insert(cmd.name, cmd);
+extern "C" {
+    static kcmd_begin: Cmd;
+    static kcmd_end: Cmd;
 }
 
-/// Unregister a command
-pub fn unregister_command(name: &str) {
-    COMMANDS.lock().remove(name);
+/// Every registered command, sorted by name
+pub fn commands() -> &'static [Cmd] {
+    unsafe {
+        let begin = core::ptr::addr_of!(kcmd_begin);
+        let end = core::ptr::addr_of!(kcmd_end);
+        let len = (end as usize - begin as usize) / core::mem::size_of::<Cmd>();
+        core::slice::from_raw_parts(begin, len)
+    }
 }
 
-/// Get echo state
-pub fn get_echo() -> bool {
-    CONSOLE_STATE.lock().echo
+/// Command called `name`
+pub fn find_command(name: &str) -> Option<&'static Cmd> {
+    let commands = commands();
+    commands
+        .binary_search_by(|cmd| cmd.name.cmp(name))
+        .ok()
+        .map(|i| &commands[i])
 }
 
-/// Set echo state
-pub fn set_echo(echo: bool) {
-    CONSOLE_STATE.lock().echo = echo;
+/// Split `line` into at most `MAX_NUM_ARGS` whitespace-separated words
+fn split_args<'a>(line: &'a str, argv: &mut [&'a str; MAX_NUM_ARGS]) -> usize {
+    let mut argc = 0;
+    for word in line.split_ascii_whitespace().take(MAX_NUM_ARGS) {
+        argv[argc] = word;
+        argc += 1;
+    }
+    argc
 }
 
-/// Get last command result
-pub fn get_last_result() -> i32 {
-    CONSOLE_STATE.lock().last_result
+/// Run one command line, returning the command's result
+///
+/// Blank lines succeed without doing anything; unknown commands fail with
+/// -1.
+pub fn run_command(line: &str) -> i32 {
+    let mut argv = [""; MAX_NUM_ARGS];
+    let argc = split_args(line, &mut argv);
+    if argc == 0 {
+        return 0;
+    }
+
+    match find_command(argv[0]) {
+        Some(cmd) if cmd.flags & CMD_AVAIL_NORMAL != 0 => {
+            (cmd.func)(argc as i32, &argv[..argc], CMD_AVAIL_NORMAL)
+        }
+        _ => {
+            println!("command not found: {}", argv[0]);
+            -1
+        }
+    }
 }
 
-/// Get abort script flag
-pub fn get_abort_script() -> bool {
-    CONSOLE_STATE.lock().abort_script
+/// What a key did to the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    /// Keep reading
+    Pending,
+
+    /// Enter: the line is complete
+    Done,
+
+    /// Ctrl-C: throw the line away
+    Cancel,
 }
 
-/// Set abort script flag
-pub fn set_abort_script(abort: bool) {
-    CONSOLE_STATE.lock().abort_script = abort;
+/// Escape sequence parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    Esc,
+    Csi,
 }
 
-/// Split a command line into arguments
-pub fn split_args(line: &str) -> Vec<String> {
-    let mut args = Vec::new();
-    let mut current = String::new();
-    let mut in_quotes = false;
+/// Line editor with history
+///
+/// Handles printable characters, backspace, Ctrl-U (kill line), Ctrl-C,
+/// and the arrow keys: left and right move the cursor, up and down recall
+/// history. Echo and redraw go to `out`.
+struct LineEditor {
+    line: [u8; LINE_LEN],
+    len: usize,
+    cursor: usize,
+    escape: Escape,
+
+    history: [[u8; LINE_LEN]; HISTORY_LEN],
+    history_len: [usize; HISTORY_LEN],
+
+    /// Lines ever added to the history
+    history_count: usize,
+
+    /// How far back the up arrow has gone, 0 for the line being edited
+    recall: usize,
+}
 
-    for ch in line.chars() {
-        match ch {
-            '"' => {
-                in_quotes = !in_quotes;
-            }
-            ' ' | '\t' if !in_quotes => {
-                if !current.is_empty() {
-                    args.push(current.clone());
-                    current.clear();
-                }
-            }
-            _ => {
-                current.push(ch);
-            }
+impl LineEditor {
+    const fn new() -> Self {
+        Self {
+            line: [0; LINE_LEN],
+            len: 0,
+            cursor: 0,
+            escape: Escape::None,
+            history: [[0; LINE_LEN]; HISTORY_LEN],
+            history_len: [0; HISTORY_LEN],
+            history_count: 0,
+            recall: 0,
         }
     }
 
-    if !current.is_empty() {
-        args.push(current);
+    /// The line so far
+    fn line(&self) -> &str {
+        core::str::from_utf8(&self.line[..self.len]).unwrap_or("")
     }
 
-    args
-}
-
-/// Parse command arguments into CmdArg structures
-pub fn parse_args(args: &[String]) -> Vec<CmdArg> {
-    args.iter()
-        .map(|s| CmdArg {
-            str: Box::leak(s.clone().into_boxed_str()),
-            u: s.parse().unwrap_or(0),
-            i: s.parse().unwrap_or(0),
-            f: s.parse().unwrap_or(0.0),
-            b: s.parse().unwrap_or(false),
-        })
-        .collect()
-}
-
-/// Execute a command line
-pub fn exec_command(line: &str) -> i32 {
-    let args = split_args(line);
-    if args.is_empty() {
-        return 0;
+    /// Start a new, empty line
+    fn reset(&mut self) {
+        self.len = 0;
+        self.cursor = 0;
+        self.escape = Escape::None;
+        self.recall = 0;
     }
 
-    let cmd_name = Box::leak(args[0].clone().into_boxed_str());
-    let commands = COMMANDS.lock();
-
-    if let Some(cmd) = commands.get(cmd_name) {
-        if let Some(func) = cmd.func {
-            let parsed_args = parse_args(&args[1..]);
-            let result = func(args.len() - 1, &parsed_args, CMD_AVAIL_NORMAL);
+    /// Handle one input byte
+    fn feed(&mut self, c: u8, out: &mut impl Write) -> Key {
+        match self.escape {
+            Escape::Esc => {
+                self.escape = if c == b'[' { Escape::Csi } else { Escape::None };
+                return Key::Pending;
+            }
+            Escape::Csi => {
+                self.escape = Escape::None;
+                match c {
+                    b'A' => self.recall_history(self.recall + 1, out),
+                    b'B' => self.recall_history(self.recall.saturating_sub(1), out),
+                    b'C' if self.cursor < self.len => {
+                        let _ = out.write_str(self.slice(self.cursor, self.cursor + 1));
+                        self.cursor += 1;
+                    }
+                    b'D' if self.cursor > 0 => {
+                        let _ = out.write_char('\x08');
+                        self.cursor -= 1;
+                    }
+                    _ => {}
+                }
+                return Key::Pending;
+            }
+            Escape::None => {}
+        }
 
-            let mut state = CONSOLE_STATE.lock();
-            state.last_result = result;
-            result
-        } else {
-            println!("Command '{}' has no function", cmd_name);
-            -1
+        match c {
+            b'\r' | b'\n' => {
+                let _ = out.write_str("\n");
+                self.add_history();
+                return Key::Done;
+            }
+            0x03 => {
+                let _ = out.write_str("^C\n");
+                return Key::Cancel;
+            }
+            0x1b => self.escape = Escape::Esc,
+            0x08 | 0x7f if self.cursor > 0 => {
+                self.line.copy_within(self.cursor..self.len, self.cursor - 1);
+                self.cursor -= 1;
+                self.len -= 1;
+                let _ = out.write_char('\x08');
+                self.redraw_tail(1, out);
+            }
+            0x15 => self.replace(&[], out),
+            0x20..=0x7e if self.len < LINE_LEN => {
+                self.line.copy_within(self.cursor..self.len, self.cursor + 1);
+                self.line[self.cursor] = c;
+                self.len += 1;
+                self.cursor += 1;
+                let _ = out.write_char(c as char);
+                self.redraw_tail(0, out);
+            }
+            _ => {}
         }
-    } else {
-        println!("Unknown command: '{}'", cmd_name);
-        -1
+        Key::Pending
     }
-}
 
-/// Initialize the console subsystem
-pub fn console_init() {
-    #[cfg(CONSOLE_ENABLE_HISTORY)]
-    {
-        let mut state = CONSOLE_STATE.lock();
-        // Initialize history with empty strings
-        state.history = vec![String::new(); HISTORY_LEN];
-        state.history_next = 0;
+    fn slice(&self, start: usize, end: usize) -> &str {
+        core::str::from_utf8(&self.line[start..end]).unwrap_or("")
     }
 
-    // Register built-in commands
-    register_builtin_commands();
-}
+    /// Reprint the line after the cursor, blank `erased` trailing cells
+    /// and move the terminal cursor back
+    fn redraw_tail(&self, erased: usize, out: &mut impl Write) {
+        let _ = out.write_str(self.slice(self.cursor, self.len));
+        for _ in 0..erased {
+            let _ = out.write_char(' ');
+        }
+        for _ in self.cursor..self.len + erased {
+            let _ = out.write_char('\x08');
+        }
+    }
 
-/// Register built-in commands
-fn register_builtin_commands() {
-    // Help command
-    register_command(Cmd {
-        name: "help",
-        help: "Show this list",
-        func: Some(cmd_help),
-        flags: CMD_AVAIL_ALWAYS,
-    });
-
-    // Echo command
-    register_command(Cmd {
-        name: "echo",
-        help: "Toggle command echo or echo text",
-        func: Some(cmd_echo),
-        flags: CMD_AVAIL_ALWAYS,
-    });
-
-    #[cfg(CONSOLE_ENABLE_HISTORY)]
-    {
-        // History command
-        register_command(Cmd {
-            name: "history",
-            help: "Show command history",
-            func: Some(cmd_history),
-            flags: CMD_AVAIL_ALWAYS,
-        });
+    /// Replace the whole line with `text`, cursor at the end
+    fn replace(&mut self, text: &[u8], out: &mut impl Write) {
+        for _ in 0..self.cursor {
+            let _ = out.write_char('\x08');
+        }
+        let old_len = self.len;
+        self.len = text.len().min(LINE_LEN);
+        self.line[..self.len].copy_from_slice(&text[..self.len]);
+        self.cursor = 0;
+        self.redraw_tail(old_len.saturating_sub(self.len), out);
+        let _ = out.write_str(self.line());
+        self.cursor = self.len;
     }
-}
 
-/// Help command implementation
-fn cmd_help(_argc: usize, _argv: &[CmdArg], _flags: u32) -> i32 {
-    println!("Available commands:");
+    /// Show the line `back` entries back in the history, 0 for empty
+    fn recall_history(&mut self, back: usize, out: &mut impl Write) {
+        let back = back.min(self.history_count.min(HISTORY_LEN));
+        if back == self.recall {
+            return;
+        }
+        self.recall = back;
 
-    let commands = COMMANDS.lock();
-    for (name, cmd) in commands.iter() {
-        if cmd.help.is_empty() {
-            println!("  {}", name);
+        if back == 0 {
+            self.replace(&[], out);
         } else {
-            println!("  {:16} - {}", name, cmd.help);
+            let slot = (self.history_count - back) % HISTORY_LEN;
+            let entry = self.history[slot];
+            self.replace(&entry[..self.history_len[slot]], out);
         }
     }
 
-    0
-}
-
-/// Echo command implementation
-fn cmd_echo(argc: usize, argv: &[CmdArg], _flags: u32) -> i32 {
-    if argc == 0 {
-        // Toggle echo
-        let echo = get_echo();
-        set_echo(!echo);
-        println!("Echo {}", if !echo { "on" } else { "off" });
-    } else {
-        // Print the arguments
-        for i in 0..argc {
-            print!("{}", argv[i].str);
-            if i < argc - 1 {
-                print!(" ");
+    /// Remember the current line unless it is blank or repeats the last one
+    fn add_history(&mut self) {
+        if self.line().trim().is_empty() {
+            return;
+        }
+        if self.history_count > 0 {
+            let last = (self.history_count - 1) % HISTORY_LEN;
+            if self.history[last][..self.history_len[last]] == self.line[..self.len] {
+                return;
             }
         }
-        println!();
-    }
 
-    0
+        let slot = self.history_count % HISTORY_LEN;
+        self.history[slot][..self.len].copy_from_slice(&self.line[..self.len]);
+        self.history_len[slot] = self.len;
+        self.history_count += 1;
+    }
 }
 
-#[cfg(CONSOLE_ENABLE_HISTORY)]
-/// History command implementation
-fn cmd_history(_argc: usize, _argv: &[CmdArg], _flags: u32) -> i32 {
-    dump_history();
-    0
-}
+/// Shell loop: read a line, run it, repeat
+fn shell() -> ! {
+    let mut editor = LineEditor::new();
+    let mut out = LogWriter;
+
+    println!("kernel shell, type 'help' for a list of commands");
+    loop {
+        editor.reset();
+        print!("{}", PROMPT);
+
+        let key = loop {
+            match crate::platform::platform_dgetc() {
+                Some(c) => match editor.feed(c, &mut out) {
+                    Key::Pending => {}
+                    key => break key,
+                },
+                None => thread::yield_current(),
+            }
+        };
 
-#[cfg(CONSOLE_ENABLE_HISTORY)]
-/// Add a line to command history
-pub fn add_history(line: &str) {
-    // Reject empty lines
-    if line.is_empty() {
-        return;
+        if key == Key::Done {
+            run_command(editor.line());
+        }
     }
+}
 
-    let mut state = CONSOLE_STATE.lock();
+extern "C" fn shell_thread_entry(_arg: usize) -> ! {
+    shell()
+}
 
-    // Check if the same as last entry
-    let last = (state.history_next + HISTORY_LEN - 1) % HISTORY_LEN;
-    if state.history[last] == line {
+/// Start the shell thread if `kernel.shell` is set
+pub fn start() {
+    if !cmdline::cmdline_get_bool(CMDLINE_SHELL, false) {
         return;
     }
 
-    // Add the new line
-    state.history[state.history_next] = line.to_string();
-    state.history_next = (state.history_next + 1) % HISTORY_LEN;
-}
-
-#[cfg(CONSOLE_ENABLE_HISTORY)]
-/// Get previous history entry
-pub fn prev_history(cursor: &mut usize) -> Option<String> {
-    let state = CONSOLE_STATE.lock();
-
-    if *cursor == state.history_next {
-        return None;
+    match thread::Thread::new_kernel(shell_thread_entry, 0, thread::PRIORITY_DEFAULT) {
+        Ok(shell_thread) => {
+            shell_thread.set_name("kshell");
+            shell_thread.start().ok();
+            log_info!("console: shell started, {} commands", commands().len());
+            thread::register_thread(shell_thread.into_ref());
+        }
+        Err(e) => {
+            log_error!("console: failed to create shell thread: {:?}", e);
+        }
     }
-
-    *cursor = (*cursor + HISTORY_LEN - 1) % HISTORY_LEN;
-    Some(state.history[*cursor].clone())
 }
 
-#[cfg(CONSOLE_ENABLE_HISTORY)]
-/// Get next history entry
-pub fn next_history(cursor: &mut usize) -> Option<String> {
-    let state = CONSOLE_STATE.lock();
-
-    let next = (*cursor + 1) % HISTORY_LEN;
-    if next == state.history_next {
-        return None;
+/// `help` console command
+fn cmd_help(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    println!("command list:");
+    for cmd in commands() {
+        println!("  {:<16}: {}", cmd.name, cmd.help);
     }
-
-    *cursor = next;
-    Some(state.history[*cursor].clone())
+    0
 }
 
-#[cfg(CONSOLE_ENABLE_HISTORY)]
-/// Dump command history
-fn dump_history() {
-    println!("Command history:");
-
-    let state = CONSOLE_STATE.lock();
-    let mut ptr = (state.history_next + HISTORY_LEN - 1) % HISTORY_LEN;
-
-    for _ in 0..HISTORY_LEN {
-        if !state.history[ptr].is_empty() {
-            println!("\t{}", state.history[ptr]);
-        }
-        ptr = (ptr + HISTORY_LEN - 1) % HISTORY_LEN;
-    }
-}
+crate::static_command!("help", "this list", cmd_help);
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::string::String;
+
+    fn feed_all(editor: &mut LineEditor, input: &[u8], out: &mut String) -> Key {
+        let mut key = Key::Pending;
+        for &c in input {
+            key = editor.feed(c, out);
+        }
+        key
+    }
 
     #[test]
     fn test_split_args() {
-        let args = split_args("hello world");
-        assert_eq!(args, vec!["hello", "world"]);
-
-        let args = split_args("  foo  bar  baz  ");
-        assert_eq!(args, vec!["foo", "bar", "baz"]);
-
-        let args = split_args("single");
-        assert_eq!(args, vec!["single"]);
+        let mut argv = [""; MAX_NUM_ARGS];
+        assert_eq!(split_args("  ps   -v\tfoo ", &mut argv), 3);
+        assert_eq!(&argv[..3], &["ps", "-v", "foo"]);
+        assert_eq!(split_args("   ", &mut argv), 0);
+    }
 
-        let args = split_args("");
-        assert!(args.is_empty());
+    #[test]
+    fn test_editing() {
+        let mut editor = LineEditor::new();
+        let mut out = String::new();
+
+        assert_eq!(feed_all(&mut editor, b"thraeds\x7f\x7f\x7f\x7feads", &mut out), Key::Pending);
+        assert_eq!(editor.line(), "threads");
+
+        // Insert in the middle with the left arrow
+        editor.reset();
+        feed_all(&mut editor, b"p\x1b[Dx", &mut out);
+        assert_eq!(editor.line(), "xp");
+
+        // Ctrl-U kills the line
+        feed_all(&mut editor, b"\x15ps", &mut out);
+        assert_eq!(editor.line(), "ps");
+        assert_eq!(editor.feed(b'\r', &mut out), Key::Done);
     }
 
     #[test]
-    fn test_parse_args() {
-        let args = vec!["123".to_string(), "-456".to_string()];
-        let parsed = parse_args(&args);
+    fn test_history() {
+        let mut editor = LineEditor::new();
+        let mut out = String::new();
+
+        for line in [&b"ps\r"[..], b"vm\r", b"vm\r", b"heap\r"] {
+            editor.reset();
+            feed_all(&mut editor, line, &mut out);
+        }
+
+        editor.reset();
+        feed_all(&mut editor, b"\x1b[A", &mut out);
+        assert_eq!(editor.line(), "heap");
+
+        // Repeated lines are kept once
+        feed_all(&mut editor, b"\x1b[A\x1b[A", &mut out);
+        assert_eq!(editor.line(), "ps");
 
-        assert_eq!(parsed[0].u, 123);
-        assert_eq!(parsed[0].i, 123);
-        assert_eq!(parsed[1].i, -456);
+        // Up stops at the oldest line, down returns to an empty line
+        feed_all(&mut editor, b"\x1b[A", &mut out);
+        assert_eq!(editor.line(), "ps");
+        feed_all(&mut editor, b"\x1b[B\x1b[B\x1b[B", &mut out);
+        assert_eq!(editor.line(), "");
     }
 
     #[test]
-    fn test_cmd_echo() {
-        let argv = [CmdArg {
-            str: "test",
-            u: 0,
-            i: 0,
-            f: 0.0,
-            b: false,
-        }];
-        let result = cmd_echo(1, &argv, 0);
-        assert_eq!(result, 0);
+    fn test_cancel() {
+        let mut editor = LineEditor::new();
+        let mut out = String::new();
+        assert_eq!(feed_all(&mut editor, b"reboot\x03", &mut out), Key::Cancel);
     }
 }
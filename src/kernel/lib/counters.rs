@@ -6,312 +6,208 @@
 
 //! Kernel Counters
 //!
-//! This module provides system-wide counter tracking for monitoring
-//! kernel metrics on a per-CPU basis.
-
-#![no_std]
-
-extern crate alloc;
-
-use alloc::collections::BTreeMap;
-use alloc::format;
-use alloc::sync::Arc;
-use alloc::vec::Vec;
-use spin::Mutex;
+//! Named, per-CPU event counters declared anywhere in the kernel with
+//! [`KCOUNTER!`](crate::KCOUNTER) or [`KCOUNTER_MAX!`](crate::KCOUNTER_MAX)
+//! and read by name from userspace with `rx_kcounter_read`.
+//!
+//! # Design
+//!
+//! Each counter places a 16-byte [`KcounterDesc`] in a `kcountdesc.<name>`
+//! section. The linker script collects these, sorted by name, between
+//! `kcountdesc_begin` and `kcountdesc_end`, so the descriptor table can be
+//! enumerated and binary searched without any registration at boot.
+//!
+//! Each counter also reserves `SMP_MAX_CPUS` slots in a
+//! `.bss.kcounter.<name>` section, collected at `kcounters_arena`. The
+//! arena is indexed CPU-major: CPU `c` owns the contiguous row
+//! `arena[c * count .. (c + 1) * count]`, so CPUs bumping the same counter
+//! never share a cache line. A counter's index is its position in the
+//! descriptor table.
+//!
+//! Updates are relaxed atomics on the caller's own slot and never take a
+//! lock. Reads walk every CPU's slot and combine them by the counter type.
 
-use crate::rustux::types::*;
+use core::sync::atomic::{AtomicI64, Ordering};
 
-/// Maximum number of CPUs supported
-pub const SMP_MAX_CPUS: usize = 64;
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
 
-/// Fixed-point shift for decimal calculations (8 bits = 256)
-const DOT8_SHIFT: u64 = 8;
+/// Longest counter name, including the terminating NUL
+pub const KCOUNTER_MAX_NAME: usize = 48;
 
-/// Counter types
-#[repr(C)]
+/// How per-CPU values combine into the counter's value
+#[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CounterType {
+pub enum KcounterType {
     /// Sum across all CPUs
     Sum = 0,
+
     /// Maximum value across all CPUs
     Max = 1,
 }
 
-/// Counter descriptor
+/// Counter descriptor, one per counter in the descriptor section
+///
+/// The layout is fixed at 16 bytes: a counter's index is its offset into
+/// the descriptor section divided by this size.
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct CounterDesc {
-    /// Counter name
-    pub name: &'static str,
+pub struct KcounterDesc {
+    /// NUL-terminated name
+    name: *const u8,
+
     /// Counter type
-    pub counter_type: CounterType,
-    /// Variable name (for introspection)
-    pub varname: &'static str,
-}
+    kind: KcounterType,
 
-/// Watched counter entry for monitoring
-#[derive(Debug)]
-struct WatchedCounter {
-    /// Descriptor for this counter
-    desc: CounterDesc,
-    /// Whether to show verbose output
-    verbose: bool,
+    _reserved: u32,
 }
 
-/// Counter arena storage
-///
-/// Stores per-CPU counter values indexed by counter ID
-pub struct CounterArena {
-    /// Per-CPU counter arrays
-    percpu_counters: [[u64; SMP_MAX_CPUS]],
-}
+// Descriptors are immutable and only point at string literals
+unsafe impl Sync for KcounterDesc {}
+
+impl KcounterDesc {
+    /// Descriptor for `name`, which must end in a NUL
+    pub const fn new(name: &'static str, kind: KcounterType) -> Self {
+        let bytes = name.as_bytes();
+        assert!(!bytes.is_empty() && bytes[bytes.len() - 1] == 0, "kcounter name must be NUL-terminated");
+        assert!(bytes.len() <= KCOUNTER_MAX_NAME, "kcounter name too long");
 
-impl CounterArena {
-    /// Create a new counter arena
-    pub const fn new() -> Self {
         Self {
-            percpu_counters: [[0; SMP_MAX_CPUS]; 0],
+            name: bytes.as_ptr(),
+            kind,
+            _reserved: 0,
         }
     }
-}
-
-/// Global counter state
-static COUNTER_STATE: Mutex<CounterState> = Mutex::new(CounterState::new());
 
-/// Internal state for counter management
-struct CounterState {
-    /// Registered counters
-    counters: Vec<CounterDesc>,
-    /// Watched counters (for monitoring)
-    watched: Vec<WatchedCounter>,
-}
-
-impl CounterState {
-    pub const fn new() -> Self {
-        Self {
-            counters: Vec::new(),
-            watched: Vec::new(),
+    /// Counter name
+    pub fn name(&self) -> &'static str {
+        unsafe {
+            let mut len = 0;
+            while *self.name.add(len) != 0 {
+                len += 1;
+            }
+            core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.name, len))
         }
     }
-}
-
-/// Register a counter descriptor
-///
-/// # Safety
-///
-/// Must be called during kernel initialization before any counters are used
-pub unsafe fn register_counter(desc: CounterDesc) -> usize {
-    let mut state = COUNTER_STATE.lock();
-    let index = state.counters.len();
-    state.counters.push(desc);
-    index
-}
-
-/// Get the number of registered counters
-pub fn get_num_counters() -> usize {
-    COUNTER_STATE.lock().counters.len()
-}
 
-/// Increment a counter on the current CPU
-///
-/// # Arguments
-///
-/// * `counter_id` - Counter ID from registration
-/// * `value` - Value to add (default 1)
-pub fn counter_add(cpu: usize, counter_id: usize, value: u64) {
-    // TODO: Implement per-CPU counter storage
-    let _ = cpu;
-    let _ = counter_id;
-    let _ = value;
-}
-
-/// Set a counter value on the current CPU
-///
-/// # Arguments
-///
-/// * `cpu` - CPU number
-/// * `counter_id` - Counter ID from registration
-/// * `value` - Value to set
-pub fn counter_set(cpu: usize, counter_id: usize, value: u64) {
-    // TODO: Implement per-CPU counter storage
-    let _ = cpu;
-    let _ = counter_id;
-    let _ = value;
+    /// Counter type
+    pub fn kind(&self) -> KcounterType {
+        self.kind
+    }
 }
 
-/// Get counter values from all CPUs
-///
-/// Returns an array of per-CPU counter values
-pub fn counter_get_all_cpus(counter_id: usize) -> [u64; SMP_MAX_CPUS] {
-    // TODO: Implement per-CPU counter storage
-    let _ = counter_id;
-    [0; SMP_MAX_CPUS]
+/// Handle to a counter, as declared by `KCOUNTER!`
+pub struct Kcounter {
+    desc: &'static KcounterDesc,
 }
 
-/// Clean up counter values (remove zeros and sort)
-///
-/// Returns only non-zero values, sorted ascending
-pub fn counters_clean_up_values(values_in: &[u64]) -> Vec<u64> {
-    let mut values: Vec<u64> = values_in
-        .iter()
-        .copied()
-        .filter(|&v| v > 0)
-        .collect();
-    values.sort();
-    values
-}
-
-/// Get a percentile from sorted values
-///
-/// Uses linear interpolation between closest ranks method.
-/// `percentage_dot8` is percentage * 256 (e.g., 25% = 64, 75% = 192)
-///
-/// # Arguments
-///
-/// * `values` - Sorted array of values
-/// * `percentage_dot8` - Percentage in fixed-point (0-256 = 0-100%)
-pub fn counters_get_percentile(values: &[u64], percentage_dot8: u64) -> u64 {
-    assert!(values.len() >= 2, "Need at least 2 values for percentile");
-
-    let count = values.len() as u64;
-    let target_dot8 = (count - 1) * percentage_dot8;
-    let low_index = (target_dot8 >> DOT8_SHIFT) as usize;
-    let high_index = low_index + 1;
-    let fraction_dot8 = target_dot8 & 0xff;
-
-    if high_index >= values.len() {
-        return values[low_index];
+impl Kcounter {
+    #[doc(hidden)]
+    pub const fn new(desc: &'static KcounterDesc) -> Self {
+        Self { desc }
     }
 
-    let delta = values[high_index].wrapping_sub(values[low_index]);
-    (values[low_index] << DOT8_SHIFT) + fraction_dot8 * delta
-}
-
-/// Check if counter values have outliers using Tukey's fences
-///
-/// Returns true if any value is outside [Q1 - 1.5*IQR, Q3 + 1.5*IQR]
-pub fn counters_has_outlier(values_in: &[u64]) -> bool {
-    let values = counters_clean_up_values(values_in);
-    if values.len() < 2 {
-        return false;
+    /// Position of the counter in the descriptor table
+    #[inline]
+    fn index(&self) -> usize {
+        let base = descriptors().as_ptr();
+        (self.desc as *const KcounterDesc as usize - base as usize)
+            / core::mem::size_of::<KcounterDesc>()
     }
 
-    // Calculate Q1 (25th percentile) and Q3 (75th percentile)
-    let q1_dot8 = counters_get_percentile(&values, 64); // 0.25 * 256
-    let q3_dot8 = counters_get_percentile(&values, 192); // 0.75 * 256
-
-    // Tukey's fences use k = 1.5
-    let k_dot8 = 384; // 1.5 * 256
-    let q_delta_dot8 = q3_dot8.wrapping_sub(q1_dot8);
-
-    let low_fence = q1_dot8 as i64 - ((k_dot8 * q_delta_dot8) >> DOT8_SHIFT) as i64;
-    let high_fence = q3_dot8 as i64 + ((k_dot8 * q_delta_dot8) >> DOT8_SHIFT) as i64;
-
-    // Check if any value is an outlier
-    for &value in &values {
-        let scaled_value = (value as i64) << DOT8_SHIFT;
-        if scaled_value < low_fence || scaled_value > high_fence {
-            return true;
-        }
+    /// This CPU's slot
+    #[inline]
+    fn slot(&self) -> &'static AtomicI64 {
+        let cpu = (percpu::current_cpu_num() as usize).min(SMP_MAX_CPUS - 1);
+        &arena()[cpu * count() + self.index()]
     }
 
-    false
-}
-
-/// Dump a single counter's values
-pub fn dump_counter(desc: &CounterDesc, verbose: bool) {
-    let values = counter_get_all_cpus(0); // TODO: Get actual counter ID
-
-    let summary = if desc.counter_type == CounterType::Max {
-        *values.iter().max().unwrap_or(&0)
-    } else {
-        values.iter().sum()
-    };
+    /// Add `delta` to a `Sum` counter
+    #[inline]
+    pub fn add(&self, delta: i64) {
+        self.slot().fetch_add(delta, Ordering::Relaxed);
+    }
 
-    println!("[{:02}] {} = {}", 0, desc.name, summary);
+    /// Raise a `Max` counter to `value` if it is larger
+    #[inline]
+    pub fn update_max(&self, value: i64) {
+        self.slot().fetch_max(value, Ordering::Relaxed);
+    }
 
-    if summary == 0 {
-        return;
+    /// Counter name
+    pub fn name(&self) -> &'static str {
+        self.desc.name()
     }
 
-    // Print per-core values if verbose or if there are outliers
-    if verbose || counters_has_outlier(&values) {
-        print!("     ");
-        for (ix, &value) in values.iter().enumerate() {
-            if value > 0 {
-                print!("[{}:{}]", ix, value);
-            }
-        }
-        println!();
+    /// Current value, combined across CPUs
+    pub fn value(&self) -> i64 {
+        value(self.index())
     }
 }
 
-/// Dump all registered counters
-pub fn dump_all_counters(verbose: bool) {
-    let state = COUNTER_STATE.lock();
-    println!("{} counters available:", state.counters.len());
-    for desc in &state.counters {
-        dump_counter(desc, verbose);
+extern "C" {
+    static kcountdesc_begin: KcounterDesc;
+    static kcountdesc_end: KcounterDesc;
+    static kcounters_arena: AtomicI64;
+}
+
+/// All counter descriptors, sorted by name
+pub fn descriptors() -> &'static [KcounterDesc] {
+    unsafe {
+        let begin = core::ptr::addr_of!(kcountdesc_begin);
+        let end = core::ptr::addr_of!(kcountdesc_end);
+        let len = (end as usize - begin as usize) / core::mem::size_of::<KcounterDesc>();
+        core::slice::from_raw_parts(begin, len)
     }
 }
 
-/// Find counters matching a prefix
-///
-/// Returns matching counter descriptors
-pub fn find_counters_by_prefix(prefix: &str) -> Vec<CounterDesc> {
-    let state = COUNTER_STATE.lock();
-    state
-        .counters
-        .iter()
-        .filter(|desc| desc.name.starts_with(prefix))
-        .copied()
-        .collect()
+/// Number of counters
+pub fn count() -> usize {
+    descriptors().len()
 }
 
-/// Binary search for upper bound of counter name
-///
-/// For finding counters starting with a given prefix
-pub fn upper_bound_counter_name(name: &str) -> usize {
-    let state = COUNTER_STATE.lock();
-    // Simple linear search for now - could be optimized with binary search
-    state
-        .counters
-        .iter()
-        .position(|desc| desc.name > name)
-        .unwrap_or(state.counters.len())
+/// Per-CPU slots of every counter
+fn arena() -> &'static [AtomicI64] {
+    unsafe { core::slice::from_raw_parts(core::ptr::addr_of!(kcounters_arena), count() * SMP_MAX_CPUS) }
 }
 
-/// Add a counter to the watch list
-///
-/// Monitored counters are periodically dumped
-pub fn watch_counter(desc: CounterDesc, verbose: bool) {
-    let mut state = COUNTER_STATE.lock();
-    state.watched.push(WatchedCounter { desc, verbose });
+/// Index of the counter called `name`
+pub fn find(name: &str) -> Option<usize> {
+    descriptors().binary_search_by(|desc| desc.name().cmp(name)).ok()
 }
 
-/// Stop watching all counters
-pub fn stop_watching_counters() {
-    let mut state = COUNTER_STATE.lock();
-    state.watched.clear();
+/// Value of the counter at `index`, combined across CPUs
+pub fn value(index: usize) -> i64 {
+    let count = count();
+    let arena = arena();
+    let per_cpu = (0..SMP_MAX_CPUS).map(|cpu| arena[cpu * count + index].load(Ordering::Relaxed));
+    combine(descriptors()[index].kind(), per_cpu)
 }
 
-/// Dump all watched counters
+/// `counters` console command
 ///
-/// Called periodically by the watcher thread
-pub fn dump_watched_counters() {
-    let state = COUNTER_STATE.lock();
-    for wc in &state.watched {
-        dump_counter(&wc.desc, wc.verbose);
+/// Lists every counter, or those whose names start with an argument.
+fn cmd_counters(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    let prefixes = &argv[1..argc as usize];
+    for (index, desc) in descriptors().iter().enumerate() {
+        let name = desc.name();
+        if prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p)) {
+            let kind = match desc.kind() {
+                KcounterType::Sum => "sum",
+                KcounterType::Max => "max",
+            };
+            crate::println!("{:<48} {:>16} {}", name, value(index), kind);
+        }
     }
+    0
 }
 
-/// Initialize the counter subsystem
-///
-/// Called during kernel initialization
-pub fn counters_init() {
-    // Per-CPU counter arrays are initialized in the BSS
-    // Wire them up here
-    // TODO: Implement proper per-CPU initialization
+crate::static_command!("counters", "show kernel counters [prefix...]", cmd_counters);
+
+/// Combine per-CPU values by counter type
+fn combine(kind: KcounterType, per_cpu: impl Iterator<Item = i64>) -> i64 {
+    match kind {
+        KcounterType::Sum => per_cpu.fold(0i64, |acc, v| acc.wrapping_add(v)),
+        KcounterType::Max => per_cpu.max().unwrap_or(0),
+    }
 }
 
 #[cfg(test)]
@@ -319,30 +215,19 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_clean_up_values() {
-        let values = vec![0, 5, 0, 10, 0, 3, 0];
-        let cleaned = counters_clean_up_values(&values);
-        assert_eq!(cleaned, vec![3, 5, 10]);
-    }
+    fn test_desc_layout() {
+        assert_eq!(core::mem::size_of::<KcounterDesc>(), 16);
 
-    #[test]
-    fn test_percentile() {
-        let values = vec![10, 20, 30, 40, 50];
-        let q1 = counters_get_percentile(&values, 64); // 25%
-        let q3 = counters_get_percentile(&values, 192); // 75%
-        // With linear interpolation, Q1 ≈ 20, Q3 ≈ 40
-        assert!(q1 >= 19 && q1 <= 21);
-        assert!(q3 >= 39 && q3 <= 41);
+        let desc = KcounterDesc::new("kernel.test.counter\0", KcounterType::Max);
+        assert_eq!(desc.name(), "kernel.test.counter");
+        assert_eq!(desc.kind(), KcounterType::Max);
     }
 
     #[test]
-    fn test_outlier_detection() {
-        // No outliers
-        let values1 = vec![10, 12, 11, 13, 10, 14, 11];
-        assert!(!counters_has_outlier(&values1));
-
-        // With outlier (100)
-        let values2 = vec![10, 12, 11, 100, 10, 14, 11];
-        assert!(counters_has_outlier(&values2));
+    fn test_combine() {
+        let values = [3i64, 0, 7, -2];
+        assert_eq!(combine(KcounterType::Sum, values.iter().copied()), 8);
+        assert_eq!(combine(KcounterType::Max, values.iter().copied()), 7);
+        assert_eq!(combine(KcounterType::Max, core::iter::empty()), 0);
     }
 }
@@ -6,343 +6,435 @@
 
 //! Crash Log
 //!
-//! This module provides crash logging functionality for kernel panics.
-//! It formats panic information including uptime, version, registers, and backtrace.
+//! Keeps the console output of a kernel panic across a warm reboot so it
+//! can be collected on the next boot, pstore style.
+//!
+//! # Design
+//!
+//! The log is written to a region of RAM that firmware leaves alone over a
+//! warm reset. The region is found, in order of preference, from
+//!
+//! 1. `kernel.crashlog=<size>@<paddr>` on the command line,
+//! 2. the first `MemoryType::Persistent` range in the boot handoff. The
+//!    UEFI loader reserves one at a fixed address, and the device tree
+//!    path reports a `ramoops` node under `/reserved-memory`.
+//!
+//! EFI variables are not used: runtime services are not mapped once the
+//! kernel owns the machine, and a panic is no time to start mapping them.
+//!
+//! Layout (little-endian):
+//!
+//! ```text
+//! Header { magic: u32 = "RXCL", version: u32, len: u32, flags: u32,
+//!          checksum: u32, reason: u32, reserved: [u32; 2] }
+//! text   { len bytes of console output }
+//! ```
+//!
+//! The panic path calls [`begin`], after which every console write is
+//! appended by [`record`], and [`finish`] seals the log with a checksum of
+//! the text. `len` is kept current as text is appended, so a log whose
+//! panic never reached `finish` (a hang or a fault in the panic path) is
+//! still recovered, flagged as unsealed.
+//!
+//! On the next boot [`init`] validates the region, copies the log to the
+//! heap and clears the region. The copy is returned by [`recovered`] and
+//! read from userspace with `rx_system_crashlog_read`.
 
-#![no_std]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 
-extern crate alloc;
+use crate::kernel::cmdline;
+use crate::kernel::handoff::{self, MemoryType};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::vm;
 
-use alloc::format;
-use alloc::string::String;
-use alloc::vec::Vec;
-use core::sync::atomic::{AtomicU64, Ordering};
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Region magic ("RXCL")
+pub const CRASHLOG_MAGIC: u32 = 0x4c43_5852;
 
-use crate::rustux::types::*;
+/// Region layout version
+pub const CRASHLOG_VERSION: u32 = 1;
 
-/// Maximum crash log size
-const MAX_CRASHLOG_SIZE: usize = 4096;
+/// Command line option naming the region
+pub const CMDLINE_CRASHLOG: &str = "kernel.crashlog";
 
-/// Architecture string
-#[cfg(target_arch = "x86_64")]
-const ARCH_NAME: &str = "x86_64";
+/// Header size in bytes
+pub const HEADER_SIZE: usize = 32;
 
-#[cfg(target_arch = "aarch64")]
-const ARCH_NAME: &str = "aarch64";
+/// Largest region used; the rest of a bigger one is left alone
+pub const CRASHLOG_MAX_SIZE: usize = 1024 * 1024;
 
-#[cfg(target_arch = "riscv64")]
-const ARCH_NAME: &str = "riscv64";
+/// The log was sealed by `finish`
+pub const FLAG_SEALED: u32 = 1 << 0;
 
-/// Interrupt frame for x86_64
-#[cfg(target_arch = "x86_64")]
+/// Output was dropped because the region filled up
+pub const FLAG_TRUNCATED: u32 = 1 << 1;
+
+/// Crash log structure
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct InterruptFrame {
-    pub cs: u64,
-    pub ip: u64,
-    pub flags: u64,
-    pub rax: u64,
-    pub rbx: u64,
-    pub rcx: u64,
-    pub rdx: u64,
-    pub rsi: u64,
-    pub rdi: u64,
-    pub rbp: u64,
-    pub user_sp: u64,
-    pub r8: u64,
-    pub r9: u64,
-    pub r10: u64,
-    pub r11: u64,
-    pub r12: u64,
-    pub r13: u64,
-    pub r14: u64,
-    pub r15: u64,
-    pub err_code: u64,
+pub struct CrashLog {
+    /// Exception frame of the fatal exception, if any
+    pub iframe: AtomicPtr<u8>,
 }
 
-/// Interrupt frame for aarch64
-#[cfg(target_arch = "aarch64")]
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct InterruptFrame {
-    pub r: [u64; 30],
-    pub lr: u64,
-    pub usp: u64,
-    pub elr: u64,
-    pub spsr: u64,
+/// Global crash log instance
+pub static crashlog: CrashLog = CrashLog {
+    iframe: AtomicPtr::new(core::ptr::null_mut()),
+};
+
+/// Record the iframe of a fatal exception for the panic path
+pub fn set_iframe(iframe: *mut u8) {
+    crashlog.iframe.store(iframe, Ordering::Release);
 }
 
-/// Crash log data
-pub struct CrashLog {
-    /// Interrupt frame (if available)
-    pub iframe: Option<&'static InterruptFrame>,
-    /// Kernel base address
-    pub base_address: u64,
+/// Iframe recorded by `set_iframe`, or null
+pub fn iframe() -> *mut u8 {
+    crashlog.iframe.load(Ordering::Acquire)
 }
 
-/// Global crash log instance
-static CRASHLOG: Mutex<CrashLog> = Mutex::new(CrashLog {
-    iframe: None,
-    base_address: 0,
-});
-
-/// Current time in nanoseconds (stub)
-fn current_time() -> i64 {
-    // TODO: Implement proper time tracking
-    0
+/// Kernel virtual address of the persistent region, 0 if there is none
+static REGION_BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// Size of the persistent region in bytes
+static REGION_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Console output is being appended to the region
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// Log recovered from the previous boot
+static RECOVERED: SpinMutex<Option<RecoveredLog>> = SpinMutex::new(None);
+
+/// A crash log left by the previous boot
+#[derive(Clone, Copy)]
+pub struct RecoveredLog {
+    /// Console output captured during the crash
+    pub text: &'static [u8],
+
+    /// Halt reason passed to `begin`
+    pub reason: u32,
+
+    /// `FLAG_*` bits
+    pub flags: u32,
 }
 
-/// Format crash log to string
-///
-/// # Arguments
-///
-/// * `out` - Output buffer
-///
-/// # Returns
-///
-/// Number of bytes written
-pub fn crashlog_to_string(out: &mut [u8]) -> usize {
-    let log = CRASHLOG.lock();
-    let mut pos = 0;
-
-    // Header
-    let header = "RUSTUX KERNEL PANIC\n\n";
-    if pos + header.len() <= out.len() {
-        out[pos..pos + header.len()].copy_from_slice(header.as_bytes());
-        pos += header.len();
-    } else {
-        return out.len();
+impl RecoveredLog {
+    /// Whether the panic path got as far as sealing the log
+    pub fn is_sealed(&self) -> bool {
+        self.flags & FLAG_SEALED != 0
     }
+}
+
+/// In-memory view of the region header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    magic: u32,
+    version: u32,
+    len: u32,
+    flags: u32,
+    checksum: u32,
+    reason: u32,
+}
 
-    // Uptime
-    let uptime_ms = current_time() / 1_000_000;
-    let uptime_str = format!("UPTIME (ms)\n{}\n\n", uptime_ms);
-    if pos + uptime_str.len() <= out.len() {
-        out[pos..pos + uptime_str.len()].copy_from_slice(uptime_str.as_bytes());
-        pos += uptime_str.len();
-    } else {
-        return out.len();
+impl Header {
+    const MAGIC: usize = 0;
+    const VERSION: usize = 4;
+    const LEN: usize = 8;
+    const FLAGS: usize = 12;
+    const CHECKSUM: usize = 16;
+    const REASON: usize = 20;
+
+    unsafe fn read(base: *const u8) -> Self {
+        Self {
+            magic: read_u32(base, Self::MAGIC),
+            version: read_u32(base, Self::VERSION),
+            len: read_u32(base, Self::LEN),
+            flags: read_u32(base, Self::FLAGS),
+            checksum: read_u32(base, Self::CHECKSUM),
+            reason: read_u32(base, Self::REASON),
+        }
     }
+}
 
-    // Version info
-    let version = format!(
-        "VERSION\narch: {}\nbuild_id: {}\ndso: id={} base={:#x} name=kernel.elf\n\n",
-        ARCH_NAME,
-        env!("CARGO_PKG_VERSION"),
-        "buildid_placeholder", // TODO: Get actual build ID
-        log.base_address
-    );
-    if pos + version.len() <= out.len() {
-        out[pos..pos + version.len()].copy_from_slice(version.as_bytes());
-        pos += version.len();
-    } else {
-        return out.len();
+/// Find the persistent region, recover any log in it and arm it for this
+/// boot
+///
+/// Called once the physmap and the heap are available.
+pub fn init() {
+    let (paddr, size) = match find_region() {
+        Some(region) => region,
+        None => {
+            log_info!("crashlog: no persistent region, crashes will not be kept");
+            return;
+        }
+    };
+
+    let size = (size as usize).min(CRASHLOG_MAX_SIZE);
+    if size <= HEADER_SIZE {
+        log_warn!("crashlog: region at {:#x} is too small ({} bytes)", paddr, size);
+        return;
+    }
+
+    let base = vm::phys_to_physmap(paddr as usize);
+    let region = unsafe { core::slice::from_raw_parts_mut(base as *mut u8, size) };
+
+    if let Some(log) = recover(region) {
+        log_warn!("crashlog: recovered {} bytes from the previous boot (reason {:#x}{})",
+            log.text.len(), log.reason, if log.is_sealed() { "" } else { ", unsealed" });
+        *RECOVERED.lock() = Some(log);
     }
 
-    // Registers
-    if let Some(iframe) = log.iframe {
-        let regs = format_registers(iframe);
-        if pos + regs.len() <= out.len() {
-            out[pos..pos + regs.len()].copy_from_slice(regs.as_bytes());
-            pos += regs.len();
-        } else {
-            return out.len();
+    // Clear the region so a clean reboot does not report the same crash again
+    unsafe { write_u32(region.as_mut_ptr(), Header::MAGIC, 0) };
+
+    REGION_SIZE.store(size, Ordering::Relaxed);
+    REGION_BASE.store(base, Ordering::Release);
+    log_info!("crashlog: {} bytes at {:#x}", size, paddr);
+}
+
+/// Locate the persistent region as (physical address, size)
+fn find_region() -> Option<(u64, u64)> {
+    if let Some(value) = cmdline::cmdline_get(CMDLINE_CRASHLOG) {
+        match parse_region(value) {
+            Some(region) => return Some(region),
+            None => {
+                log_warn!("crashlog: ignoring malformed {}={}", CMDLINE_CRASHLOG, value);
+            }
         }
     }
 
-    // Backtrace header
-    let bt_header = "BACKTRACE (up to 16 calls)\n";
-    if pos + bt_header.len() <= out.len() {
-        out[pos..pos + bt_header.len()].copy_from_slice(bt_header.as_bytes());
-        pos += bt_header.len();
-    } else {
-        return out.len();
+    handoff::get()?
+        .memory_ranges()
+        .iter()
+        .find(|r| r.mem_type == MemoryType::Persistent)
+        .map(|r| (r.base, r.length))
+}
+
+/// Parse `<size>@<paddr>`, where size may carry a `K` or `M` suffix
+fn parse_region(value: &str) -> Option<(u64, u64)> {
+    let (size, paddr) = value.split_once('@')?;
+
+    let (digits, scale) = match size.as_bytes().last()? {
+        b'k' | b'K' => (&size[..size.len() - 1], 1024),
+        b'm' | b'M' => (&size[..size.len() - 1], 1024 * 1024),
+        _ => (size, 1),
+    };
+    let size = parse_u64(digits)?.checked_mul(scale)?;
+    let paddr = parse_u64(paddr)?;
+
+    if size == 0 || paddr.checked_add(size).is_none() {
+        return None;
     }
+    Some((paddr, size))
+}
 
-    // Backtrace (placeholder)
-    let bt = format_backtrace();
-    if pos + bt.len() <= out.len() {
-        out[pos..pos + bt.len()].copy_from_slice(bt.as_bytes());
-        pos += bt.len();
-    } else {
-        return out.len();
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
     }
+}
 
-    // Footer
-    let footer = "\n";
-    if pos + footer.len() <= out.len() {
-        out[pos..pos + footer.len()].copy_from_slice(footer.as_bytes());
-        pos += footer.len();
+/// Validate the region and copy its log to the heap
+fn recover(region: &[u8]) -> Option<RecoveredLog> {
+    let header = unsafe { Header::read(region.as_ptr()) };
+    if header.magic != CRASHLOG_MAGIC || header.version != CRASHLOG_VERSION {
+        return None;
     }
 
-    pos
+    let len = header.len as usize;
+    if len > region.len() - HEADER_SIZE {
+        return None;
+    }
+    let text = &region[HEADER_SIZE..HEADER_SIZE + len];
+
+    // A sealed log must match its checksum; an unsealed one is taken as is
+    let mut flags = header.flags;
+    if flags & FLAG_SEALED != 0 && checksum(text) != header.checksum {
+        flags &= !FLAG_SEALED;
+    }
+
+    let text: &'static [u8] = Box::leak(Vec::from(text).into_boxed_slice());
+    Some(RecoveredLog { text, reason: header.reason, flags })
 }
 
-/// Format registers for x86_64
-#[cfg(target_arch = "x86_64")]
-fn format_registers(frame: &InterruptFrame) -> String {
-    format!(
-        "REGISTERS\n\
-         CS: {:#18x}\n\
-         RIP: {:#18x}\n\
-         EFL: {:#18x}\n\
-         CR2: {:#18x}\n\
-         RAX: {:#18x}\n\
-         RBX: {:#18x}\n\
-         RCX: {:#18x}\n\
-         RDX: {:#18x}\n\
-         RSI: {:#18x}\n\
-         RDI: {:#18x}\n\
-         RBP: {:#18x}\n\
-         RSP: {:#18x}\n\
-          R8: {:#18x}\n\
-          R9: {:#18x}\n\
-         R10: {:#18x}\n\
-         R11: {:#18x}\n\
-         R12: {:#18x}\n\
-         R13: {:#18x}\n\
-         R14: {:#18x}\n\
-         R15: {:#18x}\n\
-        errc: {:#18x}\n\
-        \n",
-        frame.cs,
-        frame.ip,
-        frame.flags,
-        x86_get_cr2(),
-        frame.rax,
-        frame.rbx,
-        frame.rcx,
-        frame.rdx,
-        frame.rsi,
-        frame.rdi,
-        frame.rbp,
-        frame.user_sp,
-        frame.r8,
-        frame.r9,
-        frame.r10,
-        frame.r11,
-        frame.r12,
-        frame.r13,
-        frame.r14,
-        frame.r15,
-        frame.err_code
-    )
+/// Start recording console output into the region
+///
+/// Called by the panic path once the other CPUs are stopped. Does nothing
+/// if there is no region.
+pub fn begin(reason: u32) {
+    let base = REGION_BASE.load(Ordering::Acquire);
+    if base == 0 {
+        return;
+    }
+
+    let base = base as *mut u8;
+    unsafe {
+        write_u32(base, Header::VERSION, CRASHLOG_VERSION);
+        write_u32(base, Header::LEN, 0);
+        write_u32(base, Header::FLAGS, 0);
+        write_u32(base, Header::CHECKSUM, 0);
+        write_u32(base, Header::REASON, reason);
+        write_u32(base, Header::MAGIC, CRASHLOG_MAGIC);
+    }
+    RECORDING.store(true, Ordering::Release);
 }
 
-/// Format registers for aarch64
-#[cfg(target_arch = "aarch64")]
-fn format_registers(frame: &InterruptFrame) -> String {
-    format!(
-        "REGISTERS\n\
-          x0: {:#18x}\n\
-          x1: {:#18x}\n\
-          x2: {:#18x}\n\
-          x3: {:#18x}\n\
-          x4: {:#18x}\n\
-          x5: {:#18x}\n\
-          x6: {:#18x}\n\
-          x7: {:#18x}\n\
-          x8: {:#18x}\n\
-          x9: {:#18x}\n\
-         x10: {:#18x}\n\
-         x11: {:#18x}\n\
-         x12: {:#18x}\n\
-         x13: {:#18x}\n\
-         x14: {:#18x}\n\
-         x15: {:#18x}\n\
-         x16: {:#18x}\n\
-         x17: {:#18x}\n\
-         x18: {:#18x}\n\
-         x19: {:#18x}\n\
-         x20: {:#18x}\n\
-         x21: {:#18x}\n\
-         x22: {:#18x}\n\
-         x23: {:#18x}\n\
-         x24: {:#18x}\n\
-         x25: {:#18x}\n\
-         x26: {:#18x}\n\
-         x27: {:#18x}\n\
-         x28: {:#18x}\n\
-         x29: {:#18x}\n\
-          lr: {:#18x}\n\
-         usp: {:#18x}\n\
-         elr: {:#18x}\n\
-        spsr: {:#18x}\n\
-        \n",
-        frame.r[0], frame.r[1], frame.r[2], frame.r[3], frame.r[4], frame.r[5],
-        frame.r[6], frame.r[7], frame.r[8], frame.r[9], frame.r[10], frame.r[11],
-        frame.r[12], frame.r[13], frame.r[14], frame.r[15], frame.r[16], frame.r[17],
-        frame.r[18], frame.r[19], frame.r[20], frame.r[21], frame.r[22], frame.r[23],
-        frame.r[24], frame.r[25], frame.r[26], frame.r[27], frame.r[28], frame.r[29],
-        frame.lr, frame.usp, frame.elr, frame.spsr
-    )
+/// Append console output to the region while a crash is being recorded
+///
+/// Called for every console write, so the common case is a single load.
+#[inline]
+pub fn record(s: &str) {
+    if RECORDING.load(Ordering::Relaxed) {
+        append(s.as_bytes());
+    }
 }
 
-/// Stub for non-x86 architectures
-#[cfg(not(target_arch = "x86_64"))]
-fn x86_get_cr2() -> u64 {
-    0
+fn append(bytes: &[u8]) {
+    let base = REGION_BASE.load(Ordering::Acquire) as *mut u8;
+    let capacity = REGION_SIZE.load(Ordering::Relaxed) - HEADER_SIZE;
+
+    unsafe {
+        let len = read_u32(base, Header::LEN) as usize;
+        let count = bytes.len().min(capacity - len);
+        if count < bytes.len() {
+            let flags = read_u32(base, Header::FLAGS);
+            write_u32(base, Header::FLAGS, flags | FLAG_TRUNCATED);
+        }
+
+        core::ptr::copy_nonoverlapping(bytes.as_ptr(), base.add(HEADER_SIZE + len), count);
+        write_u32(base, Header::LEN, (len + count) as u32);
+    }
 }
 
-/// Format backtrace (placeholder)
-fn format_backtrace() -> String {
-    // TODO: Implement proper backtrace capture
-    String::from("<backtrace not yet implemented>\n")
+/// Seal the log and push it out to memory
+///
+/// Called by the panic path just before the machine halts or reboots.
+pub fn finish() {
+    if !RECORDING.swap(false, Ordering::AcqRel) {
+        return;
+    }
+
+    let base = REGION_BASE.load(Ordering::Acquire) as *mut u8;
+    let size = REGION_SIZE.load(Ordering::Relaxed);
+    unsafe {
+        let len = read_u32(base, Header::LEN) as usize;
+        let text = core::slice::from_raw_parts(base.add(HEADER_SIZE), len);
+        write_u32(base, Header::CHECKSUM, checksum(text));
+
+        let flags = read_u32(base, Header::FLAGS);
+        write_u32(base, Header::FLAGS, flags | FLAG_SEALED);
+
+        clean_dcache(base as usize, size.min(HEADER_SIZE + len));
+    }
 }
 
-/// Set the interrupt frame for crash logging
-pub fn crashlog_set_iframe(iframe: &'static InterruptFrame) {
-    let mut log = CRASHLOG.lock();
-    log.iframe = Some(iframe);
+/// Log a crash
+///
+/// Records `reason` as a line of its own when a crash is being recorded.
+pub fn log_crash(reason: &str) {
+    record(reason);
+    record("\n");
 }
 
-/// Set the kernel base address
-pub fn crashlog_set_base_address(addr: u64) {
-    let mut log = CRASHLOG.lock();
-    log.base_address = addr;
+/// Log recovered from the previous boot, if there was one
+pub fn recovered() -> Option<RecoveredLog> {
+    *RECOVERED.lock()
 }
 
-/// Print crash log to console
-pub fn crashlog_print() {
-    let mut buffer = [0u8; MAX_CRASHLOG_SIZE];
-    let len = crashlog_to_string(&mut buffer);
-    let s = core::str::from_utf8(&buffer[..len]).unwrap_or("<invalid utf8>");
-    println!("{}", s);
+/// FNV-1a over the log text
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for &b in bytes {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
 }
 
-/// Initialize crash log system
-pub fn init() {
-    println!("CrashLog: initialized");
+/// Write dirty lines of the region back to RAM
+///
+/// A warm reset does not necessarily write back the data cache.
+unsafe fn clean_dcache(addr: usize, len: usize) {
+    use crate::kernel::arch::arch_traits::ArchCache;
+
+    #[cfg(target_arch = "x86_64")]
+    <crate::kernel::arch::amd64::Amd64Arch as ArchCache>::clean_dcache(addr, len);
+
+    #[cfg(target_arch = "aarch64")]
+    <crate::kernel::arch::arm64::Arm64Arch as ArchCache>::clean_dcache(addr, len);
+
+    #[cfg(target_arch = "riscv64")]
+    <crate::kernel::arch::riscv64::Riscv64Arch as ArchCache>::clean_dcache(addr, len);
+}
+
+unsafe fn read_u32(base: *const u8, off: usize) -> u32 {
+    u32::from_le(core::ptr::read_volatile(base.add(off) as *const u32))
+}
+
+unsafe fn write_u32(base: *mut u8, off: usize, value: u32) {
+    core::ptr::write_volatile(base.add(off) as *mut u32, value.to_le());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sealed_region(text: &[u8], region: &mut [u8]) {
+        let base = region.as_mut_ptr();
+        unsafe {
+            write_u32(base, Header::MAGIC, CRASHLOG_MAGIC);
+            write_u32(base, Header::VERSION, CRASHLOG_VERSION);
+            write_u32(base, Header::LEN, text.len() as u32);
+            write_u32(base, Header::FLAGS, FLAG_SEALED);
+            write_u32(base, Header::CHECKSUM, checksum(text));
+            write_u32(base, Header::REASON, 1);
+        }
+        region[HEADER_SIZE..HEADER_SIZE + text.len()].copy_from_slice(text);
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(parse_region("64K@0xf00000"), Some((0xf0_0000, 0x1_0000)));
+        assert_eq!(parse_region("1M@0x80000000"), Some((0x8000_0000, 0x10_0000)));
+        assert_eq!(parse_region("4096@65536"), Some((0x1_0000, 0x1000)));
+        assert_eq!(parse_region("0@0x1000"), None);
+        assert_eq!(parse_region("64K"), None);
+        assert_eq!(parse_region("zz@0x1000"), None);
+    }
+
     #[test]
-    fn test_crashlog_constants() {
-        assert_eq!(MAX_CRASHLOG_SIZE, 4096);
-        assert!(!ARCH_NAME.is_empty());
+    fn test_recover_sealed() {
+        let mut region = [0u8; 256];
+        sealed_region(b"*** KERNEL PANIC ***\n", &mut region);
+
+        let log = recover(&region).unwrap();
+        assert_eq!(log.text, b"*** KERNEL PANIC ***\n");
+        assert_eq!(log.reason, 1);
+        assert!(log.is_sealed());
     }
 
     #[test]
-    fn test_crashlog_format() {
-        let mut buffer = [0u8; 1024];
-        let len = crashlog_to_string(&mut buffer);
-        assert!(len > 0);
-        assert!(len < buffer.len());
-
-        let s = core::str::from_utf8(&buffer[..len]).unwrap();
-        assert!(s.contains("RUSTUX KERNEL PANIC"));
-        assert!(s.contains("UPTIME"));
-        assert!(s.contains("VERSION"));
-        assert!(s.contains("BACKTRACE"));
+    fn test_recover_rejects_bad_regions() {
+        let mut region = [0u8; 256];
+        assert!(recover(&region).is_none());
+
+        // Length past the end of the region
+        sealed_region(b"panic", &mut region);
+        unsafe { write_u32(region.as_mut_ptr(), Header::LEN, 1000) };
+        assert!(recover(&region).is_none());
     }
 
     #[test]
-    fn test_base_address() {
-        crashlog_set_base_address(0xffffffff80100000);
-        let log = CRASHLOG.lock();
-        assert_eq!(log.base_address, 0xffffffff80100000);
+    fn test_recover_corrupt_log_is_unsealed() {
+        let mut region = [0u8; 256];
+        sealed_region(b"panic", &mut region);
+        region[HEADER_SIZE] ^= 0xff;
+
+        let log = recover(&region).unwrap();
+        assert!(!log.is_sealed());
+        assert_eq!(log.text.len(), 5);
     }
 }
@@ -0,0 +1,197 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Cryptographic Random Number Generator
+//!
+//! `draw` is the kernel's source of random bytes, for itself and for
+//! userspace through `rx_cprng_draw`. It is a ChaCha20 generator seeded
+//! at boot from
+//!
+//! - the CPU's random number instructions (RDSEED/RDRAND, RNDR),
+//! - seeds passed by the boot loader: the handoff's `rng_seed`,
+//!   `/chosen/rng-seed` in the device tree and `kernel.entropy-mixin=<hex>`
+//!   on the command line,
+//! - cycle counter jitter measured at boot,
+//!
+//! and reseeded as interrupt timing accumulates in the entropy pool.
+//! Data passed to `add_entropy` is mixed into the key immediately but,
+//! as it cannot be trusted, is never credited.
+//!
+//! The locks here do not mask interrupts, so none of this may be called
+//! from interrupt context except `entropy::add_interrupt_jitter`.
+
+pub mod chacha20;
+pub mod entropy;
+pub mod prng;
+pub mod sha256;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::kernel::sync::spin::SpinMutex;
+use entropy::EntropyPool;
+use prng::Prng;
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+/// Largest `rx_cprng_draw` request
+pub const MAX_DRAW_LEN: usize = 256;
+
+/// Largest `rx_cprng_add_entropy` request
+pub const MAX_ENTROPY_LEN: usize = 256;
+
+/// Hardware RNG bytes read at boot and at each reseed
+const HW_SEED_LEN: usize = 32;
+
+/// Timer jitter samples taken at boot
+const BOOT_JITTER_SAMPLES: usize = 4096;
+
+/// Interrupt samples to accumulate before attempting a reseed
+const RESEED_SAMPLES: usize = 256;
+
+/// Pool entropy required for a reseed once the generator is seeded
+const RESEED_BITS: usize = 128;
+
+/// The global generator
+static PRNG: SpinMutex<Prng> = SpinMutex::new(Prng::unseeded());
+
+/// Entropy waiting to be folded into the generator
+static POOL: SpinMutex<EntropyPool> = SpinMutex::new(EntropyPool::new());
+
+/// Whether boot seeding has run
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Seed the generator from every boot-time source
+///
+/// Called once the command line and device tree have been parsed.
+/// `draw` calls it itself if something needs randomness earlier, in which
+/// case only the sources available at that point contribute.
+pub fn init() {
+    if INITIALIZED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    let mut pool = POOL.lock();
+
+    let mut hw = [0u8; HW_SEED_LEN];
+    let hw_len = entropy::read_hw_rng(&mut hw);
+    pool.add(&entropy::HW_RNG, &hw[..hw_len]);
+
+    if let Some(handoff) = crate::kernel::handoff::get() {
+        pool.add(&entropy::BOOT_SEED, handoff.rng_seed());
+    }
+
+    if let Some(info) = crate::kernel::dev::fdt::platform_info() {
+        pool.add(&entropy::BOOT_SEED, info.rng_seed());
+    }
+
+    if let Some(hex) = crate::kernel::cmdline::cmdline_get("kernel.entropy-mixin") {
+        let mut mixin = [0u8; 64];
+        let len = decode_hex(hex, &mut mixin);
+        pool.add(&entropy::BOOT_SEED, &mixin[..len]);
+    }
+
+    entropy::collect_timer_jitter(&mut pool, BOOT_JITTER_SAMPLES);
+
+    let (seed, bits) = pool.extract();
+    drop(pool);
+
+    let mut prng = PRNG.lock();
+    prng.add_entropy(&seed, bits);
+    if prng.is_seeded() {
+        log_info!("CPRNG: seeded ({} hw bytes)", hw_len);
+    } else {
+        log_warn!("CPRNG: only {} bits of entropy at boot", prng.entropy_bits());
+    }
+}
+
+/// Fill `out` with cryptographically secure random bytes
+pub fn draw(out: &mut [u8]) {
+    prepare();
+    PRNG.lock().draw(out);
+}
+
+/// Draw a random u64
+pub fn rand_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    draw(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// Draw a uniformly distributed integer in [0, exclusive_upper_bound)
+pub fn rand_int(exclusive_upper_bound: u64) -> u64 {
+    prepare();
+    PRNG.lock().rand_int(exclusive_upper_bound)
+}
+
+/// Mix untrusted data into the generator without crediting it
+pub fn add_entropy(data: &[u8]) {
+    PRNG.lock().add_entropy(data, 0);
+}
+
+/// Whether the generator has been credited enough entropy
+pub fn is_seeded() -> bool {
+    PRNG.lock().is_seeded()
+}
+
+/// Seed on first use and pick up any pending entropy
+fn prepare() {
+    if !INITIALIZED.load(Ordering::Acquire) {
+        init();
+    }
+    reseed_if_ready();
+}
+
+/// Fold pending interrupt jitter and hardware entropy into the generator
+fn reseed_if_ready() {
+    if entropy::jitter_pending() < RESEED_SAMPLES {
+        return;
+    }
+
+    let mut pool = POOL.lock();
+    entropy::drain_jitter(&mut pool);
+
+    let mut hw = [0u8; HW_SEED_LEN];
+    let hw_len = entropy::read_hw_rng(&mut hw);
+    pool.add(&entropy::HW_RNG, &hw[..hw_len]);
+
+    let mut prng = PRNG.lock();
+    if pool.bits() >= RESEED_BITS || (!prng.is_seeded() && pool.bits() > 0) {
+        let (seed, bits) = pool.extract();
+        prng.add_entropy(&seed, bits);
+    }
+}
+
+/// Decode hex digits into `out`, stopping at the first non-digit pair
+fn decode_hex(hex: &str, out: &mut [u8]) -> usize {
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let mut len = 0;
+    for pair in hex.as_bytes().chunks_exact(2) {
+        if len == out.len() {
+            break;
+        }
+        match (digit(pair[0]), digit(pair[1])) {
+            (Some(hi), Some(lo)) => out[len] = (hi << 4) | lo,
+            _ => break,
+        }
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_hex() {
+        let mut out = [0u8; 4];
+        assert_eq!(decode_hex("00ff7A", &mut out), 3);
+        assert_eq!(&out[..3], &[0x00, 0xff, 0x7a]);
+        assert_eq!(decode_hex("0102030405", &mut out), 4);
+        assert_eq!(decode_hex("12zz34", &mut out), 1);
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ChaCha20 (RFC 8439)
+//!
+//! Only the keystream is needed: the PRNG hands it out directly.
+
+/// Key length in bytes
+pub const KEY_SIZE: usize = 32;
+
+/// Nonce length in bytes
+pub const NONCE_SIZE: usize = 12;
+
+/// Keystream block length in bytes
+pub const BLOCK_SIZE: usize = 64;
+
+/// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+#[inline(always)]
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// Produce keystream block `counter` for `key` and `nonce`
+pub fn block(key: &[u8; KEY_SIZE], counter: u32, nonce: &[u8; NONCE_SIZE]) -> [u8; BLOCK_SIZE] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&CONSTANTS);
+    for (i, chunk) in key.chunks_exact(4).enumerate() {
+        input[4 + i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    input[12] = counter;
+    for (i, chunk) in nonce.chunks_exact(4).enumerate() {
+        input[13 + i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    let mut state = input;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; BLOCK_SIZE];
+    for (i, chunk) in out.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&state[i].wrapping_add(input[i]).to_le_bytes());
+    }
+    out
+}
+
+/// Fill `out` with keystream starting at block `counter`
+///
+/// Returns the counter of the next unused block.
+pub fn keystream(key: &[u8; KEY_SIZE], mut counter: u32, nonce: &[u8; NONCE_SIZE], out: &mut [u8]) -> u32 {
+    for chunk in out.chunks_mut(BLOCK_SIZE) {
+        let block = block(key, counter, nonce);
+        chunk.copy_from_slice(&block[..chunk.len()]);
+        counter = counter.wrapping_add(1);
+    }
+    counter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc8439_block() {
+        // RFC 8439 section 2.3.2
+        let mut key = [0u8; KEY_SIZE];
+        for (i, b) in key.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+
+        let out = block(&key, 1, &nonce);
+        assert_eq!(&out[..16], &[
+            0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15,
+            0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71, 0xc4,
+        ]);
+        assert_eq!(&out[48..], &[
+            0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9,
+            0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e,
+        ]);
+    }
+
+    #[test]
+    fn test_keystream_is_contiguous() {
+        let key = [7u8; KEY_SIZE];
+        let nonce = [1u8; NONCE_SIZE];
+        let mut out = [0u8; 100];
+        assert_eq!(keystream(&key, 5, &nonce, &mut out), 7);
+        assert_eq!(&out[..64], &block(&key, 5, &nonce)[..]);
+        assert_eq!(&out[64..], &block(&key, 6, &nonce)[..36]);
+    }
+}
@@ -6,35 +6,24 @@
 
 //! Entropy Collector
 //!
-//! This module provides entropy collection for cryptographic purposes.
-//! Different entropy sources can be registered and combined.
-
-#![no_std]
-
-extern crate alloc;
-
-use alloc::format;
-use alloc::string::String;
-use alloc::sync::Arc;
-use alloc::vec::Vec;
-use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
-use spin::Mutex;
-
-use crate::rustux::types::*;
-
-/// Maximum name length
-const MAX_NAME_LEN: usize = 32;
+//! Each entropy source is described by an `EntropyCollector`: a name and
+//! a conservative estimate of how many bits each 1000 bytes of its
+//! output carry. Output is condensed into an `EntropyPool`, which the
+//! CPRNG drains when it reseeds.
+//!
+//! Interrupt timing is sampled from interrupt context, where taking a
+//! lock is not an option, so samples are folded into a small lock-free
+//! ring and moved to the pool on the next reseed.
 
-/// Entropy VMO
-static ENTROPY_VMO: Mutex<Option<*mut u8>> = Mutex::new(None);
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
-/// Entropy loss flag
-pub static ENTROPY_WAS_LOST: AtomicBool = AtomicBool::new(false);
+use super::sha256::{Sha256, DIGEST_SIZE};
 
 /// Entropy collector
 pub struct EntropyCollector {
-    /// Collector name
-    pub name: String,
+    /// Collector name, mixed in ahead of its output
+    pub name: &'static str,
+
     /// Entropy per 1000 bytes (in bits)
     pub entropy_per_1000_bytes: usize,
 }
@@ -46,206 +35,286 @@ impl EntropyCollector {
     ///
     /// * `name` - Name of the collector
     /// * `entropy_per_1000_bytes` - Entropy per 1000 bytes in bits
-    ///
-    /// # Returns
-    ///
-    /// New collector instance
-    pub fn new(name: &str, entropy_per_1000_bytes: usize) -> Self {
+    pub const fn new(name: &'static str, entropy_per_1000_bytes: usize) -> Self {
         assert!(entropy_per_1000_bytes > 0, "Entropy rate must be positive");
         assert!(
             entropy_per_1000_bytes <= 8000,
             "Entropy rate must be <= 8000 bits/1000 bytes"
         );
 
-        println!(
-            "Entropy: Created collector '{}' ({} bits/1000 bytes)",
-            name, entropy_per_1000_bytes
-        );
-
-        Self {
-            name: name.to_string(),
-            entropy_per_1000_bytes,
-        }
+        Self { name, entropy_per_1000_bytes }
     }
 
     /// Calculate bytes needed to get desired bits of entropy
-    ///
-    /// # Arguments
-    ///
-    /// * `bits` - Desired entropy in bits
-    ///
-    /// # Returns
-    ///
-    /// Number of bytes needed
     pub fn bytes_needed(&self, bits: usize) -> usize {
         // Avoid overflow and programming errors
         assert!(bits <= 1024 * 1024, "Requested too many bits");
 
         // Round up to ensure at least the requested amount of entropy
-        (1000 * bits + self.entropy_per_1000_bytes - 1) / self.entropy_per_1000_bytes
+        (1000 * bits).div_ceil(self.entropy_per_1000_bytes)
     }
 
-    /// Collect entropy
-    ///
-    /// # Arguments
-    ///
-    /// * `data` - Entropy data
-    /// * `len` - Length of data
-    pub fn collect(&self, data: &[u8]) {
-        let _ = (data, self.entropy_per_1000_bytes);
-        // TODO: Add entropy to the global entropy pool
+    /// Entropy carried by `len` bytes of output (rounded down)
+    pub fn bits_in(&self, len: usize) -> usize {
+        len.saturating_mul(self.entropy_per_1000_bytes) / 1000
     }
 }
 
-/// Hardware RNG collector
-pub struct HwRngCollector {
-    /// Base collector
-    pub collector: EntropyCollector,
+/// CPU random number instructions (RDSEED/RDRAND, RNDR)
+pub static HW_RNG: EntropyCollector = EntropyCollector::new("hw_rng", 8000);
+
+/// Seeds passed by the boot loader
+pub static BOOT_SEED: EntropyCollector = EntropyCollector::new("boot_seed", 8000);
+
+/// Cycle counter deltas around a busy loop at boot
+pub static TIMER_JITTER: EntropyCollector = EntropyCollector::new("timer_jitter", 32);
+
+/// Cycle counter at interrupt entry, 8 bytes per sample
+pub static IRQ_JITTER: EntropyCollector = EntropyCollector::new("irq_jitter", 64);
+
+/// ============================================================================
+/// Entropy Pool
+/// ============================================================================
+
+/// Entropy credited by the pool is capped at what one extraction can carry
+const MAX_POOL_BITS: usize = DIGEST_SIZE * 8;
+
+/// Hash-based entropy pool
+pub struct EntropyPool {
+    /// Running hash of everything mixed in since the last extraction
+    hash: Sha256,
+
+    /// Entropy credited since the last extraction (bits)
+    bits: usize,
 }
 
-impl HwRngCollector {
-    /// Create a new hardware RNG collector
-    ///
-    /// # Returns
-    ///
-    /// New collector instance
-    pub fn new() -> Self {
-        Self {
-            collector: EntropyCollector::new("hw_rng", 8000), // Assume 8 bits/byte
-        }
+impl EntropyPool {
+    /// Create an empty pool
+    pub const fn new() -> Self {
+        Self { hash: Sha256::new(), bits: 0 }
     }
 
-    /// Read from hardware RNG
-    ///
-    /// # Arguments
-    ///
-    /// * `buf` - Buffer to fill
-    /// * `len` - Length of buffer
-    ///
-    /// # Returns
+    /// Mix `data` without crediting any entropy
+    pub fn mix(&mut self, data: &[u8]) {
+        self.hash.update(&(data.len() as u64).to_le_bytes());
+        self.hash.update(data);
+    }
+
+    /// Credit `bits` of entropy to what has been mixed
+    pub fn credit(&mut self, bits: usize) {
+        self.bits = (self.bits + bits).min(MAX_POOL_BITS);
+    }
+
+    /// Mix a collector's output, crediting it at the collector's rate
+    pub fn add(&mut self, collector: &EntropyCollector, data: &[u8]) {
+        self.mix(collector.name.as_bytes());
+        self.mix(data);
+        self.credit(collector.bits_in(data.len()));
+    }
+
+    /// Entropy credited since the last extraction (bits)
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Condense the pool into a seed, returning it with its credited entropy
     ///
-    /// Number of bytes read
-    pub fn read(&self, buf: &mut [u8], len: usize) -> usize {
-        // TODO: Implement actual hardware RNG reading
-        let _ = (buf, len);
-        println!("Entropy: HW RNG read requested ({} bytes)", len);
-        0
+    /// The pool restarts from a hash that is independent of the returned
+    /// seed, so uncredited input keeps contributing.
+    pub fn extract(&mut self) -> ([u8; DIGEST_SIZE], usize) {
+        let mut seed = self.hash.clone();
+        seed.update(b"extract");
+        let mut carry = core::mem::take(&mut self.hash);
+        carry.update(b"carry");
+        self.hash.update(&carry.finalize());
+
+        (seed.finalize(), core::mem::take(&mut self.bits))
     }
 }
 
-/// Jitter entropy collector
-pub struct JitterEntropyCollector {
-    /// Base collector
-    pub collector: EntropyCollector,
+impl Default for EntropyPool {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl JitterEntropyCollector {
-    /// Create a new jitter entropy collector
-    ///
-    /// # Returns
-    ///
-    /// New collector instance
-    pub fn new() -> Self {
-        Self {
-            collector: EntropyCollector::new("jitter", 1000), // Conservative estimate
+/// ============================================================================
+/// Sources
+/// ============================================================================
+
+/// Read the free-running cycle counter
+///
+/// Returns 0 on architectures without one.
+#[inline(always)]
+pub fn cycle_counter() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        crate::kernel::arch::amd64::asm::rdtsc()
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        let cnt: u64;
+        unsafe {
+            core::arch::asm!("mrs {0}, cntvct_el0", out(reg) cnt, options(nomem, nostack));
         }
+        cnt
     }
 
-    /// Read jitter entropy
-    ///
-    /// # Arguments
-    ///
-    /// * `buf` - Buffer to fill
-    /// * `len` - Length of buffer
-    ///
-    /// # Returns
-    ///
-    /// Number of bytes read
-    pub fn read(&self, buf: &mut [u8], len: usize) -> usize {
-        // TODO: Implement actual jitter entropy reading
-        let _ = (buf, len);
-        println!("Entropy: Jitter read requested ({} bytes)", len);
+    #[cfg(target_arch = "riscv64")]
+    {
+        let cnt: u64;
+        unsafe {
+            core::arch::asm!("rdtime {0}", out(reg) cnt, options(nomem, nostack));
+        }
+        cnt
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64")))]
+    {
         0
     }
 }
 
-/// Initialize entropy collection system
+/// Read from the CPU's random number instructions without blocking
 ///
-/// This function sets up the entropy VMO and initializes collectors.
-pub fn entropy_init() {
-    println!("Entropy: Initializing entropy collection system");
-
-    // TODO: Create entropy VMO
-    let mut entropy_vmo = ENTROPY_VMO.lock();
-    *entropy_vmo = None; // Will be set when VMO is created
-
-    // TODO: Initialize collectors
+/// Returns the number of bytes written, 0 if the CPU has none.
+pub fn read_hw_rng(buf: &mut [u8]) -> usize {
+    crate::kernel::dev::intel_rng::hw_rng_get_entropy(buf, false)
 }
 
-/// Get entropy VMO
-///
-/// # Returns
+/// Measure cycle counter jitter around a variable busy loop
 ///
-/// Pointer to entropy VMO data, or None if not initialized
-pub fn get_entropy_vmo() -> Option<*mut u8> {
-    *ENTROPY_VMO.lock()
+/// Adds `samples` deltas to `pool`, crediting them only if they vary
+/// (a counter that does not tick, or ticks in lockstep, carries none).
+pub fn collect_timer_jitter(pool: &mut EntropyPool, samples: usize) {
+    let mut batch = [0u8; 64];
+    let mut last = 0u64;
+    let mut varied = 0usize;
+    let mut spin = 0u64;
+
+    pool.mix(TIMER_JITTER.name.as_bytes());
+    for i in 0..samples {
+        let start = cycle_counter();
+        for _ in 0..(spin & 0x3f) + 1 {
+            core::hint::spin_loop();
+        }
+        let delta = cycle_counter().wrapping_sub(start);
+        spin = spin.rotate_left(7) ^ delta;
+
+        if delta != last {
+            varied += 1;
+        }
+        last = delta;
+
+        let slot = (i % 8) * 8;
+        batch[slot..slot + 8].copy_from_slice(&delta.to_le_bytes());
+        if slot == 56 {
+            pool.mix(&batch);
+        }
+    }
+    pool.mix(&batch);
+
+    pool.credit(TIMER_JITTER.bits_in(varied * 8));
 }
 
-/// Set entropy VMO
-///
-/// # Arguments
+/// ============================================================================
+/// Interrupt Jitter
+/// ============================================================================
+
+/// Number of ring slots
+const JITTER_SLOTS: usize = 64;
+
+/// Samples folded into each slot since the last drain
+static JITTER: [AtomicU64; JITTER_SLOTS] = [const { AtomicU64::new(0) }; JITTER_SLOTS];
+
+/// Samples taken since the last drain
+static JITTER_SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+/// Record the arrival time of interrupt `vector`
 ///
-/// * `vmo` - VMO pointer
-pub fn set_entropy_vmo(vmo: *mut u8) {
-    let mut entropy_vmo = ENTROPY_VMO.lock();
-    *entropy_vmo = Some(vmo);
+/// Safe to call from interrupt context: it takes no locks and never
+/// drops a sample, it only folds it into a slot.
+#[inline]
+pub fn add_interrupt_jitter(vector: u32) {
+    let now = cycle_counter();
+    let n = JITTER_SAMPLES.fetch_add(1, Ordering::Relaxed);
+    let sample = now.rotate_left((n % 64) as u32) ^ ((vector as u64) << 48);
+    JITTER[n % JITTER_SLOTS].fetch_xor(sample, Ordering::Relaxed);
 }
 
-/// Mark entropy as lost
-pub fn entropy_mark_lost() {
-    ENTROPY_WAS_LOST.store(true, Ordering::Release);
-    println!("Entropy: Entropy loss detected!");
+/// Interrupt samples waiting to be drained
+pub fn jitter_pending() -> usize {
+    JITTER_SAMPLES.load(Ordering::Relaxed)
 }
 
-/// Check if entropy was lost
-pub fn entropy_was_lost() -> bool {
-    ENTROPY_WAS_LOST.load(Ordering::Acquire)
+/// Move pending interrupt samples into `pool`
+pub fn drain_jitter(pool: &mut EntropyPool) {
+    let samples = JITTER_SAMPLES.swap(0, Ordering::Relaxed);
+    if samples == 0 {
+        return;
+    }
+
+    let mut buf = [0u8; JITTER_SLOTS * 8];
+    for (chunk, slot) in buf.chunks_exact_mut(8).zip(JITTER.iter()) {
+        chunk.copy_from_slice(&slot.swap(0, Ordering::Relaxed).to_le_bytes());
+    }
+    pool.mix(IRQ_JITTER.name.as_bytes());
+    pool.mix(&buf);
+    pool.credit(IRQ_JITTER.bits_in(samples * 8));
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_collector_creation() {
-        let collector = EntropyCollector::new("test", 4000);
-        assert_eq!(collector.name, "test");
-        assert_eq!(collector.entropy_per_1000_bytes, 4000);
-    }
-
     #[test]
     fn test_bytes_needed() {
         let collector = EntropyCollector::new("test", 1000); // 1 bit/byte
         assert_eq!(collector.bytes_needed(256), 256);
-        assert_eq!(collector.bytes_needed(1024), 1024);
+        assert_eq!(EntropyCollector::new("test", 3000).bytes_needed(256), 86);
+        assert_eq!(collector.bits_in(256), 256);
+        assert_eq!(IRQ_JITTER.bits_in(8), 0);
+        assert_eq!(IRQ_JITTER.bits_in(8000), 512);
     }
 
     #[test]
-    fn test_hw_rng_collector() {
-        let collector = HwRngCollector::new();
-        assert_eq!(collector.collector.name, "hw_rng");
-        assert_eq!(collector.collector.entropy_per_1000_bytes, 8000);
+    fn test_pool_credit_and_extract() {
+        let mut pool = EntropyPool::new();
+        pool.add(&HW_RNG, &[0xaa; 16]);
+        assert_eq!(pool.bits(), 128);
+        pool.add(&HW_RNG, &[0xbb; 64]);
+        assert_eq!(pool.bits(), MAX_POOL_BITS);
+
+        let (first, bits) = pool.extract();
+        assert_eq!(bits, MAX_POOL_BITS);
+        assert_eq!(pool.bits(), 0);
+
+        // Extraction changes the pool even with nothing new mixed in
+        let (second, bits) = pool.extract();
+        assert_eq!(bits, 0);
+        assert_ne!(first, second);
     }
 
     #[test]
-    fn test_jitter_collector() {
-        let collector = JitterEntropyCollector::new();
-        assert_eq!(collector.collector.name, "jitter");
-        assert_eq!(collector.collector.entropy_per_1000_bytes, 1000);
+    fn test_pool_depends_on_input() {
+        let mut a = EntropyPool::new();
+        let mut b = EntropyPool::new();
+        a.mix(b"one");
+        b.mix(b"two");
+        assert_ne!(a.extract().0, b.extract().0);
     }
 
     #[test]
-    fn test_constants() {
-        assert_eq!(MAX_NAME_LEN, 32);
+    fn test_interrupt_jitter_drain() {
+        for vector in 0..200 {
+            add_interrupt_jitter(vector);
+        }
+        assert!(jitter_pending() >= 200);
+
+        let mut pool = EntropyPool::new();
+        drain_jitter(&mut pool);
+        assert!(pool.bits() > 0);
+        assert_eq!(jitter_pending(), 0);
     }
 }
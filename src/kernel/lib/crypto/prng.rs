@@ -0,0 +1,161 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Pseudo-Random Number Generator (PRNG)
+//!
+//! A ChaCha20 keystream generator with fast key erasure: every draw
+//! first derives the next key from the current one, so a later
+//! compromise of the state does not reveal earlier output. Entropy is
+//! mixed in by hashing it together with the current key.
+//!
+//! The generator only counts entropy; policy on how much is enough
+//! before handing out bytes lives with the caller (`crypto::draw`).
+
+use super::chacha20::{self, KEY_SIZE, NONCE_SIZE};
+use super::sha256::Sha256;
+
+/// Credited entropy required before the generator counts as seeded
+pub const MIN_SEED_BITS: usize = 256;
+
+/// Credited entropy is capped at the key size
+const MAX_CREDIT_BITS: usize = KEY_SIZE * 8;
+
+/// ChaCha20-based PRNG
+pub struct Prng {
+    /// Current key
+    key: [u8; KEY_SIZE],
+
+    /// Draw counter, used as the ChaCha20 nonce
+    draws: u64,
+
+    /// Entropy credited so far (bits)
+    entropy_bits: usize,
+}
+
+impl Prng {
+    /// Create a generator with no entropy
+    pub const fn unseeded() -> Self {
+        Self {
+            key: [0; KEY_SIZE],
+            draws: 0,
+            entropy_bits: 0,
+        }
+    }
+
+    /// Create a generator from `seed`, crediting `bits` of entropy
+    pub fn new(seed: &[u8], bits: usize) -> Self {
+        let mut prng = Self::unseeded();
+        prng.add_entropy(seed, bits);
+        prng
+    }
+
+    /// Mix `data` into the key, crediting `bits` of entropy
+    pub fn add_entropy(&mut self, data: &[u8], bits: usize) {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.key);
+        hasher.update(data);
+        self.key = hasher.finalize();
+        self.entropy_bits = (self.entropy_bits + bits).min(MAX_CREDIT_BITS);
+    }
+
+    /// Entropy credited so far (bits, capped at the key size)
+    pub fn entropy_bits(&self) -> usize {
+        self.entropy_bits
+    }
+
+    /// Whether enough entropy has been credited
+    pub fn is_seeded(&self) -> bool {
+        self.entropy_bits >= MIN_SEED_BITS
+    }
+
+    /// Fill `out` with pseudo-random bytes
+    pub fn draw(&mut self, out: &mut [u8]) {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[..8].copy_from_slice(&self.draws.to_le_bytes());
+        self.draws = self.draws.wrapping_add(1);
+
+        // Block 0 becomes the next key; output starts at block 1
+        let next = chacha20::block(&self.key, 0, &nonce);
+        chacha20::keystream(&self.key, 1, &nonce, out);
+        self.key.copy_from_slice(&next[..KEY_SIZE]);
+    }
+
+    /// Draw a random u64
+    pub fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.draw(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Generate a random integer in [0, exclusive_upper_bound)
+    ///
+    /// Uses rejection sampling, so the result is unbiased.
+    pub fn rand_int(&mut self, exclusive_upper_bound: u64) -> u64 {
+        assert!(exclusive_upper_bound != 0, "Upper bound cannot be zero");
+
+        let bits = 64 - (exclusive_upper_bound - 1).leading_zeros();
+        let mask = if bits == 64 { u64::MAX } else { (1u64 << bits) - 1 };
+        loop {
+            let v = self.next_u64() & mask;
+            if v < exclusive_upper_bound {
+                return v;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deterministic_for_seed() {
+        let mut a = Prng::new(b"seed", 0);
+        let mut b = Prng::new(b"seed", 0);
+        let mut c = Prng::new(b"other", 0);
+
+        let (mut x, mut y, mut z) = ([0u8; 80], [0u8; 80], [0u8; 80]);
+        a.draw(&mut x);
+        b.draw(&mut y);
+        c.draw(&mut z);
+        assert_eq!(x, y);
+        assert_ne!(x, z);
+
+        // Consecutive draws differ
+        a.draw(&mut y);
+        assert_ne!(x, y);
+    }
+
+    #[test]
+    fn test_entropy_changes_output() {
+        let mut a = Prng::new(b"seed", 0);
+        let mut b = Prng::new(b"seed", 0);
+        b.add_entropy(&[1], 0);
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    #[test]
+    fn test_seeded_threshold() {
+        let mut prng = Prng::unseeded();
+        assert!(!prng.is_seeded());
+        prng.add_entropy(&[0; 16], 128);
+        assert!(!prng.is_seeded());
+        prng.add_entropy(&[0; 16], 1000);
+        assert!(prng.is_seeded());
+        assert_eq!(prng.entropy_bits(), MAX_CREDIT_BITS);
+    }
+
+    #[test]
+    fn test_rand_int_bounds() {
+        let mut prng = Prng::new(b"bounds", 0);
+        for bound in [1u64, 2, 3, 100, 1 << 40, u64::MAX] {
+            for _ in 0..32 {
+                assert!(prng.rand_int(bound) < bound);
+            }
+        }
+        assert_eq!(prng.rand_int(1), 0);
+    }
+}
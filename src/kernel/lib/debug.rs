@@ -7,7 +7,8 @@
 //! Debug Utilities
 //!
 //! This module provides debugging utilities for the kernel including
-//! panic handling, hex dumping, and stack canary support.
+//! panic handling and hex dumping. Stack canary support lives in
+//! [`crate::kernel::lib::ssp`].
 
 #![no_std]
 
@@ -87,30 +88,6 @@ pub fn panic_no_format(msg: &str) -> ! {
     platform_halt();
 }
 
-/// Stack canary failure handler
-///
-/// This is called when stack smashing is detected.
-///
-/// # Safety
-///
-/// This function should never return.
-#[no_mangle]
-pub extern "C" fn __stack_chk_fail() -> ! {
-    panic_no_format("stack canary corrupted!\n");
-}
-
-/// Choose a random stack guard value
-///
-/// # Returns
-///
-/// A random value to use as stack guard
-pub fn choose_stack_guard() -> u64 {
-    // TODO: Try to get entropy from hardware RNG
-    // For now, use a "randomish" value
-    let guard = 0xdeadbeef_00ff_00ffu64 ^ (0xDEADBEEF_u64);
-    guard
-}
-
 /// Platform-specific panic start notification
 fn platform_panic_start() {
     // TODO: Implement platform-specific panic handling
@@ -304,12 +281,6 @@ mod tests {
         spin(1);
     }
 
-    #[test]
-    fn test_stack_guard() {
-        let guard = choose_stack_guard();
-        assert_ne!(guard, 0);
-    }
-
     #[test]
     fn test_constants() {
         assert_eq!(USEC_PER_NSEC, 1000);
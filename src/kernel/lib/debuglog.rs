@@ -6,20 +6,27 @@
 
 //! Debug Log
 //!
-//! This module provides a circular buffer debug log implementation
-//! for kernel diagnostics and logging.
-
-#![no_std]
-
-extern crate alloc;
-
-use alloc::collections::LinkedList;
-use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use core::sync::atomic::compiler_fence;
-use spin::Mutex;
-
+//! One ring of log records shared by the kernel's logging macros and
+//! debuglog objects. Writers append records and drop the oldest ones to
+//! make room. Each reader keeps its own position and skips ahead to the
+//! oldest record when the writers lap it.
+//!
+//! # Record Format
+//!
+//! A record is a [`DlogHeader`] followed by `datalen` bytes of text,
+//! padded to 4 bytes in the ring. The header word packs the space the
+//! record takes in the ring (`fifolen`) and the bytes a reader gets
+//! (`readlen`). The header carries the record's metadata: when it was
+//! written, on which CPU, by which process and thread, and its level in
+//! the low bits of `flags`.
+
+use crate::kernel::percpu;
+use crate::kernel::sync::spin::SpinLockIrqSave;
+use crate::kernel::thread::{self, TID_INVALID};
+use crate::kernel::timer;
 use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// Debug log size (128KB)
 const DLOG_SIZE: usize = 128 * 1024;
@@ -28,13 +35,19 @@ const DLOG_SIZE: usize = 128 * 1024;
 const DLOG_MASK: usize = DLOG_SIZE - 1;
 
 /// Maximum data size per record (224 bytes)
-const DLOG_MAX_DATA: usize = 224;
+pub const DLOG_MAX_DATA: usize = 224;
 
 /// Minimum record size (header only)
-const DLOG_MIN_RECORD: usize = 8;
+pub const DLOG_MIN_RECORD: usize = core::mem::size_of::<DlogHeader>();
 
 /// Maximum record size (header + max data)
-const DLOG_MAX_RECORD: usize = DLOG_MIN_RECORD + DLOG_MAX_DATA;
+pub const DLOG_MAX_RECORD: usize = DLOG_MIN_RECORD + DLOG_MAX_DATA;
+
+/// Level of the record, a [`LogLevel`](crate::kernel::debug::LogLevel)
+pub const DLOG_FLAG_LEVEL_MASK: u16 = 0x7;
+
+/// Written by userspace through a debuglog object
+pub const DLOG_FLAG_USER: u16 = 1 << 8;
 
 /// Assert that DLOG_SIZE is a power of two
 const _: () = assert!(DLOG_SIZE & DLOG_MASK == 0, "DLOG_SIZE must be power of two");
@@ -46,28 +59,51 @@ const _: () = assert!(DLOG_MAX_RECORD <= DLOG_SIZE, "DLOG_MAX_RECORD too large")
 const _: () = assert!(DLOG_MAX_RECORD & 3 == 0, "DLOG_MAX_RECORD must be 4-byte aligned");
 
 /// Debug log header
-#[repr(C, packed)]
-#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct DlogHeader {
     /// Header word containing record info
     pub header: u32,
     /// Data length in bytes
     pub datalen: u16,
-    /// Flags
+    /// Flags: the level and `DLOG_FLAG_*` bits
     pub flags: u16,
-    /// Timestamp
+    /// Nanoseconds since boot
     pub timestamp: u64,
-    /// Process ID
+    /// Process ID, 0 for the kernel
     pub pid: u64,
-    /// Thread ID
+    /// Thread ID, 0 before threads are running
     pub tid: u64,
+    /// CPU the record was written on
+    pub cpu: u32,
+    /// Reserved
+    pub reserved: u32,
+}
+
+impl DlogHeader {
+    /// Header for a record written now, on this CPU by this thread
+    pub fn new(flags: u16) -> Self {
+        // Before per-CPU data is set up the CPU and thread can't be read
+        let (cpu, tid) = if percpu::num_cpus() == 0 {
+            (0, TID_INVALID)
+        } else {
+            (percpu::current_cpu_num(), thread::current_thread_id())
+        };
+        let pid = if tid == TID_INVALID { 0 } else { thread::try_current_pid().unwrap_or(0) };
+        Self { header: 0, datalen: 0, flags, timestamp: timer::current_time(), pid, tid, cpu, reserved: 0 }
+    }
+
+    /// Level bits of `flags`
+    pub fn level(&self) -> u8 {
+        (self.flags & DLOG_FLAG_LEVEL_MASK) as u8
+    }
 }
 
 // Header manipulation macros
 const DLOG_HDR_FIFOLEN_SHIFT: u32 = 18;
 const DLOG_HDR_FIFOLEN_MASK: u32 = 0x3FFF << DLOG_HDR_FIFOLEN_SHIFT;
 const DLOG_HDR_READLEN_SHIFT: u32 = 2;
-const DLOG_HDR_READLEN_MASK: u32 = (DLOG_MAX_RECORD as u32) << DLOG_HDR_READLEN_SHIFT;
+const DLOG_HDR_READLEN_MASK: u32 = 0xFFF << DLOG_HDR_READLEN_SHIFT;
 
 /// Set FIFO length in header word
 #[inline]
@@ -93,198 +129,157 @@ pub const fn dlog_hdr_set(fifolen: u32, readlen: usize) -> u32 {
     dlog_hdr_set_fifolen(fifolen) | ((readlen as u32) << DLOG_HDR_READLEN_SHIFT)
 }
 
+/// Split a record read with [`Dlog::read`] into its header and text
+pub fn parse_record(record: &[u8]) -> Option<(DlogHeader, &[u8])> {
+    if record.len() < DLOG_MIN_RECORD {
+        return None;
+    }
+    let header = unsafe { core::ptr::read_unaligned(record.as_ptr() as *const DlogHeader) };
+    let data = record.get(DLOG_MIN_RECORD..DLOG_MIN_RECORD + header.datalen as usize)?;
+    Some((header, data))
+}
+
 /// Debug log reader
+///
+/// Where a reader is in the ring; it starts at the oldest record.
+#[derive(Debug, Clone, Copy, Default)]
 pub struct DlogReader {
-    /// Pointer to the log
-    log: *const Dlog,
     /// Current read position
     tail: u64,
-    /// Notification callback
-    notify: Option<fn(*mut u8)>,
-    /// Cookie for notification callback
-    cookie: *mut u8,
 }
 
-unsafe impl Send for DlogReader {}
-
-/// Debug log structure
-pub struct Dlog {
-    /// Lock for log access
-    lock: Mutex<()>,
+/// Ring state, under the log's lock
+struct Ring {
     /// Head pointer (next write position)
-    head: AtomicU64,
+    head: u64,
     /// Tail pointer (oldest record)
-    tail: AtomicU64,
+    tail: u64,
     /// Log data buffer
     data: [u8; DLOG_SIZE],
+}
+
+impl Ring {
+    /// Copy `bytes` into the ring at `pos`, wrapping at the end
+    fn copy_in(&mut self, pos: u64, bytes: &[u8]) {
+        let offset = pos as usize & DLOG_MASK;
+        let first = bytes.len().min(DLOG_SIZE - offset);
+        self.data[offset..offset + first].copy_from_slice(&bytes[..first]);
+        self.data[..bytes.len() - first].copy_from_slice(&bytes[first..]);
+    }
+
+    /// Copy bytes out of the ring at `pos`, wrapping at the end
+    fn copy_out(&self, pos: u64, bytes: &mut [u8]) {
+        let offset = pos as usize & DLOG_MASK;
+        let first = bytes.len().min(DLOG_SIZE - offset);
+        bytes[..first].copy_from_slice(&self.data[offset..offset + first]);
+        let len = bytes.len();
+        bytes[first..].copy_from_slice(&self.data[..len - first]);
+    }
+
+    /// Header word of the record at `pos`
+    fn header_word(&self, pos: u64) -> u32 {
+        let mut word = [0u8; 4];
+        self.copy_out(pos, &mut word);
+        u32::from_le_bytes(word)
+    }
+}
+
+/// Debug log structure
+pub struct Dlog {
+    /// The ring; interrupts are off while it's held, as interrupt
+    /// handlers log too
+    ring: SpinLockIrqSave<Ring>,
     /// Panic mode flag
     panic: AtomicBool,
-    /// List of readers
-    readers: Mutex<LinkedList<Arc<DlogReader>>>,
 }
 
 /// Global debug log instance
-static mut DLOG_DATA: [u8; DLOG_SIZE] = [0; DLOG_SIZE];
-
 pub static DLOG: Dlog = Dlog::new();
 
 impl Dlog {
     pub const fn new() -> Self {
         Self {
-            lock: Mutex::new(()),
-            head: AtomicU64::new(0),
-            tail: AtomicU64::new(0),
-            data: [0; DLOG_SIZE],
+            ring: SpinLockIrqSave::new(Ring { head: 0, tail: 0, data: [0; DLOG_SIZE] }),
             panic: AtomicBool::new(false),
-            readers: Mutex::new(LinkedList::new()),
         }
     }
 
-    /// Write data to the debug log
-    pub fn write(&self, flags: u16, data: &[u8]) -> Result<(), i32> {
+    /// Write a record stamped now with `flags`
+    pub fn write(&self, flags: u16, data: &[u8]) -> Result {
+        self.write_stamped(DlogHeader::new(flags), data)
+    }
+
+    /// Write a record with the metadata in `header`
+    ///
+    /// Fails with `OUT_OF_RANGE` for more than `DLOG_MAX_DATA` bytes and
+    /// `BAD_STATE` after a panic, when the console is written directly.
+    pub fn write_stamped(&self, mut header: DlogHeader, data: &[u8]) -> Result {
         if data.len() > DLOG_MAX_DATA {
-            return Err(-1); // ZX_ERR_OUT_OF_RANGE
+            return Err(RX_ERR_OUT_OF_RANGE);
         }
 
         if self.panic.load(Ordering::Acquire) {
-            return Err(-2); // ZX_ERR_BAD_STATE
+            return Err(RX_ERR_BAD_STATE);
         }
 
         // Calculate wire size (4-byte aligned)
         let wiresize = DLOG_MIN_RECORD + ((data.len() + 3) & !3);
-
-        // Prepare header before taking lock
-        let header = DlogHeader {
-            header: dlog_hdr_set(wiresize as u32, DLOG_MIN_RECORD + data.len()),
-            datalen: data.len() as u16,
-            flags,
-            timestamp: 0, // TODO: current_time()
-            pid: 0,      // TODO: get_current_thread()->user_pid
-            tid: 0,      // TODO: get_current_thread()->user_tid
+        header.header = dlog_hdr_set(wiresize as u32, DLOG_MIN_RECORD + data.len());
+        header.datalen = data.len() as u16;
+        let header_bytes = unsafe {
+            core::slice::from_raw_parts(&header as *const DlogHeader as *const u8, DLOG_MIN_RECORD)
         };
 
-        let _guard = self.lock.lock();
+        let mut ring = self.ring.lock();
 
         // Discard old records until we have space
-        let head = self.head.load(Ordering::Acquire);
-        let mut tail = self.tail.load(Ordering::Acquire);
-
-        while (head - tail) > (DLOG_SIZE - wiresize) as u64 {
-            let offset = (tail as usize) & DLOG_MASK;
-            let header_word = u32::from_le_bytes(
-                self.data[offset..offset + 4]
-                    .try_into()
-                    .unwrap()
-            );
-            tail += dlog_hdr_get_fifolen(header_word) as u64;
-            self.tail.store(tail, Ordering::Release);
+        while ring.head - ring.tail > (DLOG_SIZE - wiresize) as u64 {
+            let tail = ring.tail;
+            ring.tail += dlog_hdr_get_fifolen(ring.header_word(tail)) as u64;
         }
 
-        // Write the record
-        let offset = (head as usize) & DLOG_MASK;
-        let header_bytes = unsafe {
-            core::slice::from_raw_parts(
-                &header as *const DlogHeader as *const u8,
-                core::mem::size_of::<DlogHeader>()
-            )
-        };
-
-        if DLOG_SIZE - offset >= wiresize {
-            // Everything fits in one write
-            self.data[offset..offset + header_bytes.len()].copy_from_slice(header_bytes);
-            self.data[offset + header_bytes.len()..offset + header_bytes.len() + data.len()]
-                .copy_from_slice(data);
-        } else if DLOG_SIZE - offset < header_bytes.len() {
-            // Header wraps around
-            let remaining = DLOG_SIZE - offset;
-            self.data[offset..].copy_from_slice(&header_bytes[..remaining]);
-            self.data[..header_bytes.len() - remaining].copy_from_slice(&header_bytes[remaining..]);
-            self.data[header_bytes.len() - remaining..].copy_from_slice(data);
-        } else {
-            // Data wraps around
-            let fifospace = DLOG_SIZE - offset - header_bytes.len();
-            self.data[offset..offset + header_bytes.len()].copy_from_slice(header_bytes);
-            let data_offset = offset + header_bytes.len();
-            self.data[data_offset..].copy_from_slice(&data[..fifospace]);
-            self.data[..data.len() - fifospace].copy_from_slice(&data[fifospace..]);
-        }
-
-        self.head.store(head + wiresize as u64, Ordering::Release);
-
-        // TODO: Notify readers
-        compiler_fence(Ordering::Release);
-
+        let head = ring.head;
+        ring.copy_in(head, header_bytes);
+        ring.copy_in(head + DLOG_MIN_RECORD as u64, data);
+        ring.head = head + wiresize as u64;
         Ok(())
     }
 
-    /// Read from the debug log
-    pub fn read(&self, reader: &mut DlogReader, buffer: &mut [u8]) -> Result<usize, i32> {
+    /// A reader starting at the oldest record
+    pub fn reader(&self) -> DlogReader {
+        DlogReader { tail: self.ring.lock().tail }
+    }
+
+    /// Read the next record into `buffer`
+    ///
+    /// Returns the record's size: its header and text, which
+    /// [`parse_record`] splits. Fails with `SHOULD_WAIT` when the reader
+    /// is at the newest record and `BUFFER_TOO_SMALL` unless `buffer`
+    /// holds `DLOG_MAX_RECORD` bytes.
+    pub fn read(&self, reader: &mut DlogReader, buffer: &mut [u8]) -> Result<usize> {
         if buffer.len() < DLOG_MAX_RECORD {
-            return Err(-1); // ZX_ERR_BUFFER_TOO_SMALL
+            return Err(RX_ERR_BUFFER_TOO_SMALL);
         }
 
-        let _guard = self.lock.lock();
-
-        let head = self.head.load(Ordering::Acquire);
-        let mut rtail = reader.tail;
+        let ring = self.ring.lock();
 
         // Check if reader has been lapped
-        if (head - self.tail.load(Ordering::Acquire)) < (head - rtail) {
-            rtail = self.tail.load(Ordering::Acquire);
-            reader.tail = rtail;
+        if ring.head - ring.tail < ring.head - reader.tail {
+            reader.tail = ring.tail;
         }
 
-        if rtail == head {
-            return Err(-2); // ZX_ERR_SHOULD_WAIT
+        if reader.tail == ring.head {
+            return Err(RX_ERR_SHOULD_WAIT);
         }
 
-        let offset = (rtail as usize) & DLOG_MASK;
-        let header_word = u32::from_le_bytes(
-            self.data[offset..offset + 4]
-                .try_into()
-                .unwrap()
-        );
-
+        let header_word = ring.header_word(reader.tail);
         let actual = dlog_hdr_get_readlen(header_word);
-
-        if DLOG_SIZE - offset >= actual {
-            buffer[..actual].copy_from_slice(&self.data[offset..offset + actual]);
-        } else {
-            let fifospace = DLOG_SIZE - offset;
-            buffer[..fifospace].copy_from_slice(&self.data[offset..]);
-            buffer[fifospace..actual].copy_from_slice(&self.data[..actual - fifospace]);
-        }
-
+        ring.copy_out(reader.tail, &mut buffer[..actual]);
         reader.tail += dlog_hdr_get_fifolen(header_word) as u64;
 
         Ok(actual)
     }
 
-    /// Initialize a reader
-    pub fn reader_init(&self, notify: Option<fn(*mut u8)>, cookie: *mut u8) -> Arc<DlogReader> {
-        let _guard = self.lock.lock();
-        let tail = self.tail.load(Ordering::Acquire);
-        let head = self.head.load(Ordering::Acquire);
-
-        let reader = Arc::new(DlogReader {
-            log: self as *const Dlog,
-            tail,
-            notify,
-            cookie,
-        });
-
-        // Notify if there's already data
-        if tail != head {
-            if let Some(notify_fn) = notify {
-                notify_fn(cookie);
-            }
-        }
-
-        self.readers.lock().push_back(reader.clone());
-
-        reader
-    }
-
     /// Enable panic mode (bluescreen)
     pub fn set_panic(&self) {
         self.panic.store(true, Ordering::Release);
@@ -292,56 +287,15 @@ impl Dlog {
 }
 
 /// Write to debug log
-pub fn dlog_write(flags: u16, data: &[u8]) -> Result<(), i32> {
+pub fn dlog_write(flags: u16, data: &[u8]) -> Result {
     DLOG.write(flags, data)
 }
 
-/// Write a formatted string to the debug log
-pub fn dlog_printf(flags: u16, format: &str) {
-    // TODO: Implement proper printf formatting
-    let _ = flags;
-    let _ = DLOG.write(0, format.as_bytes());
-}
-
-/// Initialize debug log bypass for early console output
-pub fn dlog_bypass_init_early() {
-    // TODO: Read compile-time switch
-}
-
-/// Initialize debug log bypass from cmdline
-pub fn dlog_bypass_init() {
-    // TODO: Read kernel.bypass-debuglog cmdline option
-}
-
-/// Check if debug log bypass is enabled
-pub fn dlog_bypass() -> bool {
-    false
-}
-
-/// Write to serial console (bypassing debug log)
-pub fn dlog_serial_write(data: &[u8]) {
-    if dlog_bypass() {
-        // Direct to serial with spinlock
-        // TODO: __kernel_serial_write
-        let _ = data;
-    } else {
-        // Use mutex for proper synchronization
-        // TODO: platform_dputs_thread
-        let _ = data;
-    }
-}
-
 /// Initialize bluescreen (panic) mode
+///
+/// Stops writes to the ring, whose lock the panicking CPU may hold.
 pub fn dlog_bluescreen_init() {
     DLOG.set_panic();
-
-    // TODO: udisplay_bind_gfxconsole
-    // TODO: Print panic information
-}
-
-/// Shutdown debug log threads
-pub fn dlog_shutdown() {
-    // TODO: Implement thread shutdown
 }
 
 #[cfg(test)]
@@ -357,32 +311,53 @@ mod tests {
 
         assert_eq!(dlog_hdr_get_fifolen(header), fifolen);
         assert_eq!(dlog_hdr_get_readlen(header), readlen);
+
+        let header = dlog_hdr_set(DLOG_MAX_RECORD as u32, DLOG_MAX_RECORD);
+        assert_eq!(dlog_hdr_get_readlen(header), DLOG_MAX_RECORD);
+    }
+
+    #[test]
+    fn test_header_layout() {
+        assert_eq!(core::mem::size_of::<DlogHeader>(), 40);
     }
 
     #[test]
     fn test_write_read_cycle() {
+        static LOG: Dlog = Dlog::new();
         let data = b"Test log message";
-        let flags = 0x01;
 
-        // Write
-        let result = DLOG.write(flags, data);
-        assert!(result.is_ok());
+        let mut reader = LOG.reader();
+        assert!(LOG.write(0x2, data).is_ok());
 
-        // Read
-        let reader = DLOG.reader_init(None, core::ptr::null_mut());
         let mut buffer = [0u8; DLOG_MAX_RECORD];
-        let mut reader_clone = DlogReader {
-            log: core::ptr::null(),
-            tail: reader.tail,
-            notify: None,
-            cookie: core::ptr::null_mut(),
-        };
+        let len = LOG.read(&mut reader, &mut buffer).unwrap();
+        assert_eq!(len, DLOG_MIN_RECORD + data.len());
+
+        let (header, text) = parse_record(&buffer[..len]).unwrap();
+        assert_eq!(header.level(), 0x2);
+        assert_eq!(text, data);
+        assert_eq!(LOG.read(&mut reader, &mut buffer), Err(RX_ERR_SHOULD_WAIT));
+    }
 
-        let result = DLOG.read(&mut reader_clone, &mut buffer);
-        assert!(result.is_ok());
+    #[test]
+    fn test_lapped_reader() {
+        static LOG: Dlog = Dlog::new();
+        let data = [b'x'; DLOG_MAX_DATA];
+
+        let mut reader = LOG.reader();
+        for _ in 0..(DLOG_SIZE / DLOG_MAX_RECORD) * 2 {
+            LOG.write(0, &data).unwrap();
+        }
 
-        // Verify data (note: header is included in buffer)
-        let actual_len = result.unwrap();
-        assert!(actual_len >= data.len());
+        // The reader skips to the oldest record left, and every record
+        // after it reads back whole
+        let mut buffer = [0u8; DLOG_MAX_RECORD];
+        let mut records = 0;
+        while let Ok(len) = LOG.read(&mut reader, &mut buffer) {
+            assert_eq!(parse_record(&buffer[..len]).unwrap().1, &data[..]);
+            records += 1;
+        }
+        assert_eq!(records, DLOG_SIZE / DLOG_MAX_RECORD);
+        assert_eq!(LOG.write(0, &[0; DLOG_MAX_DATA + 1]), Err(RX_ERR_OUT_OF_RANGE));
     }
 }
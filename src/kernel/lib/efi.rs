@@ -0,0 +1,511 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! EFI Runtime Services
+//!
+//! Calls into the firmware after ExitBootServices: variables and the
+//! real-time clock. ResetSystem is left to the platform's ACPI and PSCI
+//! paths.
+//!
+//! # Mapping
+//!
+//! The loader passes the memory map entries marked `EFI_MEMORY_RUNTIME`
+//! through the handoff. [`init`] gives each one an address in the
+//! `KERNEL_EFI_BASE` window, maps it there in a kernel address space of
+//! its own and calls SetVirtualAddressMap once, while the firmware can
+//! still run from the identity map. Protection and caching follow the
+//! region's type and attributes:
+//!
+//! - runtime code without `EFI_MEMORY_XP` is read-execute, everything
+//!   else read-write (read-only with `EFI_MEMORY_RO`)
+//! - write-back regions are cached; otherwise write-combining,
+//!   write-through or uncached, in that order of preference
+//!
+//! Each call switches to that address space, whose kernel half is shared
+//! with every other, and back. Calls are serialized by one lock, as the
+//! UEFI specification requires for non-reentrant services.
+//!
+//! Only the x86-64 UEFI loader passes runtime regions, so other boot
+//! paths have no runtime services and every call fails with
+//! `RX_ERR_NOT_SUPPORTED`.
+
+use crate::kernel::handoff::{self, EfiRuntimeRange};
+use crate::kernel::mmu;
+use crate::kernel::object::vmo::CachePolicy;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::vm::aspace::AddressSpace;
+use crate::kernel::vm::layout::{MemProt, PAddr, VAddr, PAGE_SIZE};
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+
+// Import logging macros
+use crate::{log_error, log_info, log_warn};
+
+/// Longest variable name accepted, in UCS-2 characters without the NUL
+pub const VARIABLE_NAME_MAX: usize = 256;
+
+/// Largest variable value accepted
+pub const VARIABLE_DATA_MAX: usize = 32 * 1024;
+
+/// EFI memory types
+const EFI_RUNTIME_SERVICES_CODE: u32 = 5;
+const EFI_MEMORY_MAPPED_IO: u32 = 11;
+const EFI_MEMORY_MAPPED_IO_PORT_SPACE: u32 = 12;
+
+/// EFI_MEMORY_* attribute bits
+const EFI_MEMORY_WC: u64 = 1 << 1;
+const EFI_MEMORY_WT: u64 = 1 << 2;
+const EFI_MEMORY_WB: u64 = 1 << 3;
+const EFI_MEMORY_XP: u64 = 1 << 14;
+const EFI_MEMORY_RO: u64 = 1 << 17;
+
+/// EFI_MEMORY_DESCRIPTOR version passed to SetVirtualAddressMap
+const DESCRIPTOR_VERSION: u32 = 1;
+
+/// Offset of RuntimeServices in EFI_SYSTEM_TABLE
+const SYSTEM_TABLE_RUNTIME_SERVICES: usize = 88;
+
+/// High bit of an EFI_STATUS: an error rather than a warning
+const EFI_ERROR_BIT: usize = 1 << (usize::BITS - 1);
+
+/// EFI_STATUS error codes
+const EFI_INVALID_PARAMETER: usize = EFI_ERROR_BIT | 2;
+const EFI_UNSUPPORTED: usize = EFI_ERROR_BIT | 3;
+const EFI_BUFFER_TOO_SMALL: usize = EFI_ERROR_BIT | 5;
+const EFI_WRITE_PROTECTED: usize = EFI_ERROR_BIT | 8;
+const EFI_OUT_OF_RESOURCES: usize = EFI_ERROR_BIT | 9;
+const EFI_NOT_FOUND: usize = EFI_ERROR_BIT | 14;
+const EFI_SECURITY_VIOLATION: usize = EFI_ERROR_BIT | 26;
+
+/// EFI_GUID in its in-memory byte order
+pub type Guid = [u8; 16];
+
+/// EFI_TIME
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EfiTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub pad1: u8,
+    pub nanosecond: u32,
+    /// Minutes from UTC, or 2047 for local time
+    pub time_zone: i16,
+    /// EFI_TIME_ADJUST_DAYLIGHT and EFI_TIME_IN_DAYLIGHT bits
+    pub daylight: u8,
+    pub pad2: u8,
+}
+
+/// EFI_TIME_CAPABILITIES
+#[repr(C)]
+#[derive(Default)]
+struct EfiTimeCapabilities {
+    resolution: u32,
+    accuracy: u32,
+    sets_to_zero: u8,
+}
+
+/// EFI_MEMORY_DESCRIPTOR
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct MemoryDescriptor {
+    efi_type: u32,
+    pad: u32,
+    physical_start: u64,
+    virtual_start: u64,
+    page_count: u64,
+    attribute: u64,
+}
+
+type GetTimeFn = unsafe extern "efiapi" fn(*mut EfiTime, *mut EfiTimeCapabilities) -> usize;
+type SetVirtualAddressMapFn =
+    unsafe extern "efiapi" fn(usize, usize, u32, *const MemoryDescriptor) -> usize;
+type GetVariableFn =
+    unsafe extern "efiapi" fn(*const u16, *const Guid, *mut u32, *mut usize, *mut u8) -> usize;
+type SetVariableFn =
+    unsafe extern "efiapi" fn(*const u16, *const Guid, u32, usize, *const u8) -> usize;
+
+/// Start of EFI_RUNTIME_SERVICES, up to the last service used
+#[repr(C)]
+struct RuntimeServicesTable {
+    header: [u8; 24],
+    get_time: GetTimeFn,
+    set_time: usize,
+    get_wakeup_time: usize,
+    set_wakeup_time: usize,
+    set_virtual_address_map: SetVirtualAddressMapFn,
+    convert_pointer: usize,
+    get_variable: GetVariableFn,
+    get_next_variable_name: usize,
+    set_variable: SetVariableFn,
+}
+
+/// Runtime services after SetVirtualAddressMap
+struct Runtime {
+    /// Maps the runtime regions at their virtual addresses
+    aspace: AddressSpace,
+    get_time: GetTimeFn,
+    get_variable: GetVariableFn,
+    set_variable: SetVariableFn,
+}
+
+impl Runtime {
+    /// Call into the firmware on the runtime services address space
+    fn call<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(target_arch = "x86_64")]
+        {
+            use crate::kernel::arch::amd64::mmu::{read_cr3, write_cr3};
+
+            let previous = read_cr3();
+            unsafe { write_cr3(self.aspace.root_phys() as u64) };
+            let result = f();
+            unsafe { write_cr3(previous) };
+            result
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            f()
+        }
+    }
+}
+
+static RUNTIME: SpinMutex<Option<Runtime>> = SpinMutex::new(None);
+
+/// Protection and caching for a runtime region
+fn region_mapping(region: &EfiRuntimeRange) -> (MemProt, CachePolicy) {
+    let attr = region.attribute;
+
+    let prot = if region.efi_type == EFI_RUNTIME_SERVICES_CODE && attr & EFI_MEMORY_XP == 0 {
+        MemProt::Execute
+    } else if attr & EFI_MEMORY_RO != 0 {
+        MemProt::Read
+    } else {
+        MemProt::ReadWrite
+    };
+
+    let mmio = matches!(region.efi_type, EFI_MEMORY_MAPPED_IO | EFI_MEMORY_MAPPED_IO_PORT_SPACE);
+    let cache = if attr & EFI_MEMORY_WB != 0 && !mmio {
+        CachePolicy::Default
+    } else if attr & EFI_MEMORY_WC != 0 && !mmio {
+        CachePolicy::WriteCombining
+    } else if attr & EFI_MEMORY_WT != 0 && !mmio {
+        CachePolicy::WriteThrough
+    } else {
+        CachePolicy::Uncached
+    };
+
+    (prot, cache)
+}
+
+/// Convert an EFI_STATUS to a kernel error
+fn status_to_result(status: usize) -> Result {
+    match status {
+        // Warnings have the high bit clear and mean success
+        s if s & EFI_ERROR_BIT == 0 => Ok(()),
+        EFI_INVALID_PARAMETER => Err(RX_ERR_INVALID_ARGS),
+        EFI_UNSUPPORTED => Err(RX_ERR_NOT_SUPPORTED),
+        EFI_BUFFER_TOO_SMALL => Err(RX_ERR_BUFFER_TOO_SMALL),
+        EFI_WRITE_PROTECTED | EFI_SECURITY_VIOLATION => Err(RX_ERR_ACCESS_DENIED),
+        EFI_OUT_OF_RESOURCES => Err(RX_ERR_NO_RESOURCES),
+        EFI_NOT_FOUND => Err(RX_ERR_NOT_FOUND),
+        _ => Err(RX_ERR_IO),
+    }
+}
+
+/// Build the virtual memory map handed to SetVirtualAddressMap
+///
+/// Regions are laid out back to back from `base` in the order given.
+/// Returns the number of descriptors filled.
+fn build_virtual_map(regions: &[EfiRuntimeRange], base: VAddr, out: &mut [MemoryDescriptor]) -> usize {
+    let mut next = base as u64;
+    let mut count = 0;
+    for (region, desc) in regions.iter().zip(out.iter_mut()) {
+        *desc = MemoryDescriptor {
+            efi_type: region.efi_type,
+            pad: 0,
+            physical_start: region.phys_start,
+            virtual_start: next,
+            page_count: region.page_count,
+            attribute: region.attribute,
+        };
+        next += region.page_count * PAGE_SIZE as u64;
+        count += 1;
+    }
+    count
+}
+
+/// Translate a physical address inside a runtime region to its virtual one
+fn translate(map: &[MemoryDescriptor], phys: u64) -> Option<u64> {
+    map.iter()
+        .find(|desc| phys >= desc.physical_start
+            && phys < desc.physical_start + desc.page_count * PAGE_SIZE as u64)
+        .map(|desc| desc.virtual_start + (phys - desc.physical_start))
+}
+
+/// Virtual window the runtime regions are mapped into
+#[cfg(target_arch = "x86_64")]
+fn window() -> Option<(VAddr, usize)> {
+    use crate::kernel::vm::layout::amd64::{KERNEL_EFI_BASE, KERNEL_EFI_SIZE};
+    Some((KERNEL_EFI_BASE, KERNEL_EFI_SIZE))
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn window() -> Option<(VAddr, usize)> {
+    None
+}
+
+/// Map the runtime regions and switch the firmware to virtual mode
+pub fn init() {
+    let handoff = match handoff::get() {
+        Some(handoff) if handoff.system_table != 0 && !handoff.efi_runtime().is_empty() => handoff,
+        _ => {
+            log_info!("EFI: no runtime services");
+            return;
+        }
+    };
+
+    let (base, size) = match window() {
+        Some(window) => window,
+        None => {
+            log_warn!("EFI: runtime services are not supported on this architecture");
+            return;
+        }
+    };
+
+    let regions = handoff.efi_runtime();
+    let mut map = [MemoryDescriptor {
+        efi_type: 0,
+        pad: 0,
+        physical_start: 0,
+        virtual_start: 0,
+        page_count: 0,
+        attribute: 0,
+    }; handoff::HANDOFF_MAX_EFI_RUNTIME];
+    let count = build_virtual_map(regions, base, &mut map);
+    let map = &map[..count];
+
+    let total: u64 = map.iter().map(|desc| desc.page_count * PAGE_SIZE as u64).sum();
+    if total > size as u64 {
+        log_error!("EFI: runtime regions ({} bytes) don't fit the window", total);
+        return;
+    }
+
+    let aspace = match AddressSpace::new_kernel() {
+        Ok(aspace) => aspace,
+        Err(err) => {
+            log_error!("EFI: failed to create address space: {:?}", err);
+            return;
+        }
+    };
+    for (region, desc) in regions.iter().zip(map) {
+        let (prot, cache) = region_mapping(region);
+        if let Err(err) = aspace.map_with_cache_policy(
+            desc.virtual_start as VAddr,
+            desc.physical_start as PAddr,
+            desc.page_count as usize,
+            prot,
+            cache,
+        ) {
+            log_error!("EFI: failed to map {:#x}: {:?}", desc.physical_start, err);
+            return;
+        }
+    }
+    aspace.flush_tlb();
+
+    // The runtime services table is read through the identity map: its
+    // function pointers are virtual once SetVirtualAddressMap returns
+    let runtime_phys = unsafe {
+        let system_table = mmu::phys_to_virt(handoff.system_table);
+        *((system_table + SYSTEM_TABLE_RUNTIME_SERVICES) as *const u64)
+    };
+    if translate(map, runtime_phys).is_none() {
+        log_error!("EFI: runtime services table {:#x} is outside the runtime regions", runtime_phys);
+        return;
+    }
+    let table = mmu::phys_to_virt(runtime_phys) as *const RuntimeServicesTable;
+
+    let status = unsafe {
+        ((*table).set_virtual_address_map)(
+            core::mem::size_of_val(map),
+            core::mem::size_of::<MemoryDescriptor>(),
+            DESCRIPTOR_VERSION,
+            map.as_ptr(),
+        )
+    };
+    if let Err(err) = status_to_result(status) {
+        log_error!("EFI: SetVirtualAddressMap failed: {:#x} ({})", status, err);
+        return;
+    }
+
+    let runtime = unsafe {
+        Runtime {
+            aspace,
+            get_time: (*table).get_time,
+            get_variable: (*table).get_variable,
+            set_variable: (*table).set_variable,
+        }
+    };
+    *RUNTIME.lock() = Some(runtime);
+
+    log_info!("EFI: runtime services at {:#x} ({} regions, {} KB)",
+        base, count, total / 1024);
+}
+
+/// Whether runtime services are available
+pub fn is_available() -> bool {
+    RUNTIME.lock().is_some()
+}
+
+/// Copy a variable name into a NUL-terminated buffer
+fn terminated_name(name: &[u16], buf: &mut [u16; VARIABLE_NAME_MAX + 1]) -> Result {
+    if name.is_empty() || name.len() > VARIABLE_NAME_MAX || name.contains(&0) {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    buf[..name.len()].copy_from_slice(name);
+    buf[name.len()] = 0;
+    Ok(())
+}
+
+/// Read a variable into `data`
+///
+/// Returns its attributes and size. If `data` is too small, fails with
+/// `RX_ERR_BUFFER_TOO_SMALL`; the size is still returned through `size_out`.
+pub fn get_variable(
+    name: &[u16],
+    vendor: &Guid,
+    data: &mut [u8],
+    size_out: &mut usize,
+) -> Result<u32> {
+    let mut name_buf = [0u16; VARIABLE_NAME_MAX + 1];
+    terminated_name(name, &mut name_buf)?;
+
+    let runtime = RUNTIME.lock();
+    let runtime = runtime.as_ref().ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    let mut attributes = 0u32;
+    let mut size = data.len();
+    let status = runtime.call(|| unsafe {
+        (runtime.get_variable)(name_buf.as_ptr(), vendor, &mut attributes, &mut size, data.as_mut_ptr())
+    });
+    *size_out = size;
+    status_to_result(status)?;
+    Ok(attributes)
+}
+
+/// Write a variable; empty `data` deletes it
+pub fn set_variable(name: &[u16], vendor: &Guid, attributes: u32, data: &[u8]) -> Result {
+    if data.len() > VARIABLE_DATA_MAX {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+    let mut name_buf = [0u16; VARIABLE_NAME_MAX + 1];
+    terminated_name(name, &mut name_buf)?;
+
+    let runtime = RUNTIME.lock();
+    let runtime = runtime.as_ref().ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    let status = runtime.call(|| unsafe {
+        (runtime.set_variable)(name_buf.as_ptr(), vendor, attributes, data.len(), data.as_ptr())
+    });
+    status_to_result(status)
+}
+
+/// Read the firmware's real-time clock
+pub fn get_time() -> Result<EfiTime> {
+    let runtime = RUNTIME.lock();
+    let runtime = runtime.as_ref().ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    let mut time = EfiTime::default();
+    let mut capabilities = EfiTimeCapabilities::default();
+    let status = runtime.call(|| unsafe { (runtime.get_time)(&mut time, &mut capabilities) });
+    status_to_result(status)?;
+    Ok(time)
+}
+
+fn cmd_efi(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    if !is_available() {
+        crate::println!("efi: no runtime services");
+        return 0;
+    }
+
+    match get_time() {
+        Ok(t) => {
+            crate::println!(
+                "time: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                t.year, t.month, t.day, t.hour, t.minute, t.second
+            );
+        }
+        Err(err) => {
+            crate::println!("time: error {}", err);
+            return -1;
+        }
+    }
+    0
+}
+
+crate::static_command!("efi", "show the EFI runtime clock", cmd_efi);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(efi_type: u32, phys_start: u64, page_count: u64, attribute: u64) -> EfiRuntimeRange {
+        EfiRuntimeRange { phys_start, page_count, attribute, efi_type, reserved: 0 }
+    }
+
+    #[test]
+    fn test_region_mapping() {
+        let code = region(EFI_RUNTIME_SERVICES_CODE, 0, 1, EFI_MEMORY_WB);
+        assert_eq!(region_mapping(&code), (MemProt::Execute, CachePolicy::Default));
+
+        let code_xp = region(EFI_RUNTIME_SERVICES_CODE, 0, 1, EFI_MEMORY_WB | EFI_MEMORY_XP);
+        assert_eq!(region_mapping(&code_xp), (MemProt::ReadWrite, CachePolicy::Default));
+
+        let data_ro = region(6, 0, 1, EFI_MEMORY_WB | EFI_MEMORY_RO);
+        assert_eq!(region_mapping(&data_ro), (MemProt::Read, CachePolicy::Default));
+
+        let mmio = region(EFI_MEMORY_MAPPED_IO, 0, 1, EFI_MEMORY_WB);
+        assert_eq!(region_mapping(&mmio), (MemProt::ReadWrite, CachePolicy::Uncached));
+    }
+
+    #[test]
+    fn test_virtual_map() {
+        let regions = [
+            region(EFI_RUNTIME_SERVICES_CODE, 0x7f00_0000, 4, EFI_MEMORY_WB),
+            region(6, 0x7e00_0000, 2, EFI_MEMORY_WB),
+        ];
+        let empty = MemoryDescriptor {
+            efi_type: 0,
+            pad: 0,
+            physical_start: 0,
+            virtual_start: 0,
+            page_count: 0,
+            attribute: 0,
+        };
+        let mut map = [empty; 4];
+        let count = build_virtual_map(&regions, 0xFFFF_FFEF_0000_0000, &mut map);
+        assert_eq!(count, 2);
+        assert_eq!(map[0].virtual_start, 0xFFFF_FFEF_0000_0000);
+        assert_eq!(map[1].virtual_start, 0xFFFF_FFEF_0000_4000);
+
+        let map = &map[..count];
+        assert_eq!(translate(map, 0x7e00_1010), Some(0xFFFF_FFEF_0000_5010));
+        assert_eq!(translate(map, 0x7e00_2000), None);
+    }
+
+    #[test]
+    fn test_status_to_result() {
+        assert_eq!(status_to_result(0), Ok(()));
+        // Warnings are success
+        assert_eq!(status_to_result(4), Ok(()));
+        assert_eq!(status_to_result(EFI_NOT_FOUND), Err(RX_ERR_NOT_FOUND));
+        assert_eq!(status_to_result(EFI_BUFFER_TOO_SMALL), Err(RX_ERR_BUFFER_TOO_SMALL));
+        assert_eq!(status_to_result(EFI_WRITE_PROTECTED), Err(RX_ERR_ACCESS_DENIED));
+    }
+}
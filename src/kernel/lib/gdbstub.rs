@@ -0,0 +1,872 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! GDB Stub
+//!
+//! A GDB remote serial protocol server on the debug UART, for source-level
+//! debugging of the kernel under QEMU.
+//!
+//! Boot with `kernel.gdb=serial` and the kernel stops early in boot and
+//! waits for GDB on the serial console:
+//!
+//! ```text
+//! qemu-system-x86_64 ... -serial tcp::1234,server
+//! gdb target/x86_64-unknown-none/release/rustux -ex 'target remote :1234'
+//! ```
+//!
+//! # Supported
+//!
+//! - Register read and write for the stopped CPU
+//! - Memory read and write. Addresses are translated through the live
+//!   page tables and accessed through the physmap, so an unmapped address
+//!   is an error rather than a nested fault, and software breakpoints can
+//!   be written into read-only kernel text.
+//! - Software breakpoints (`Z0`) and hardware breakpoints (`Z1`, x86-64
+//!   and ARM64 only)
+//! - Single step, using the trap flag on x86-64 and `MDSCR_EL1.SS` on
+//!   ARM64. RISC-V has no supervisor-mode step, so the stub plants
+//!   temporary breakpoints on the possible next instructions.
+//! - Thread listing from the thread registry. Only the stopped thread has
+//!   registers.
+//!
+//! Breakpoint and debug exceptions taken in kernel mode enter the stub.
+//! Other CPUs keep running while one is stopped, and a running kernel
+//! can't be interrupted from GDB (Ctrl-C), so stop it with a breakpoint or
+//! the shell's `gdb` command.
+//!
+//! # Command Line
+//!
+//! - `kernel.gdb=serial` - Enable the stub on the debug UART
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::kernel::cmdline;
+use crate::kernel::percpu;
+use crate::kernel::thread::{self, ThreadId, TID_INVALID};
+use crate::kernel::vm::{self, PAGE_SIZE};
+use crate::platform::{platform_pgetc, platform_pputc};
+
+use crate::{log_info, log_warn};
+
+#[cfg(target_arch = "x86_64")]
+mod amd64;
+#[cfg(target_arch = "x86_64")]
+use amd64 as arch;
+
+#[cfg(target_arch = "aarch64")]
+mod arm64;
+#[cfg(target_arch = "aarch64")]
+use arm64 as arch;
+
+#[cfg(target_arch = "riscv64")]
+mod riscv64;
+#[cfg(target_arch = "riscv64")]
+use riscv64 as arch;
+
+pub use arch::Frame;
+
+/// Stop signal for breakpoints and steps
+pub const SIGTRAP: u8 = 5;
+
+/// Largest packet in either direction, advertised to GDB
+const PACKET_SIZE: usize = 4096;
+
+/// Software breakpoints GDB can have inserted at once
+const MAX_BREAKPOINTS: usize = 64;
+
+/// Hardware breakpoint slots tracked, whatever the CPU has
+const MAX_HW_BREAKPOINTS: usize = 4;
+
+/// Thread ID reported when the CPU isn't running a registered thread,
+/// as in early boot
+const BOOT_THREAD: ThreadId = 0x7fff_ffff;
+
+/// `OWNER` when no CPU is in the stub
+const NO_CPU: u32 = u32::MAX;
+
+/// Set once `kernel.gdb=serial` has been seen
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// CPU currently talking to GDB, or `NO_CPU`
+static OWNER: AtomicU32 = AtomicU32::new(NO_CPU);
+
+/// Stub state, only touched by the `OWNER` CPU
+static mut STUB: Stub = Stub::new();
+
+/// Incoming packet, only touched by the `OWNER` CPU
+static mut PACKET: [u8; PACKET_SIZE] = [0; PACKET_SIZE];
+
+// ============================================================================
+// Entry Points
+// ============================================================================
+
+/// Start the stub if `kernel.gdb=serial` is on the command line, then stop
+/// in it to wait for GDB
+///
+/// Memory access goes through the physmap, so this must run after it is
+/// set up.
+pub fn init() {
+    match cmdline::cmdline_get("kernel.gdb") {
+        None => return,
+        Some("serial") => {}
+        Some(transport) => {
+            log_warn!("gdb: unsupported transport '{}', only 'serial' is available", transport);
+            return;
+        }
+    }
+
+    arch::init();
+    ACTIVE.store(true, Ordering::Release);
+
+    log_info!("gdb: waiting for the debugger on the serial console");
+    arch::breakpoint();
+}
+
+/// Whether the stub is enabled
+pub fn enabled() -> bool {
+    ACTIVE.load(Ordering::Acquire)
+}
+
+/// Hand a kernel-mode breakpoint or debug exception to GDB
+///
+/// Returns once GDB resumes the kernel, with `frame` updated to resume
+/// from. Returns false straight away, leaving the exception to the
+/// caller, when the stub isn't enabled or the exception was raised by the
+/// stub itself.
+pub fn handle_exception(frame: &mut Frame, signal: u8) -> bool {
+    if !enabled() {
+        return false;
+    }
+
+    let cpu = percpu::current_cpu_num();
+    if OWNER.load(Ordering::Acquire) == cpu {
+        return false;
+    }
+    while OWNER
+        .compare_exchange(NO_CPU, cpu, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+
+    // SAFETY: OWNER gives this CPU exclusive use of the stub state
+    let stub = unsafe { &mut *core::ptr::addr_of_mut!(STUB) };
+    let packet = unsafe { &mut *core::ptr::addr_of_mut!(PACKET) };
+
+    stub.stop(frame);
+
+    // GDB is waiting to hear why we stopped, unless this is the first stop
+    // and it hasn't attached yet
+    if stub.running {
+        stub.reply.clear();
+        stub.stop_reply(signal);
+        send(stub.reply.as_bytes());
+        stub.running = false;
+    }
+
+    let step = loop {
+        let len = receive(packet);
+        stub.reply.clear();
+        match stub.command(frame, signal, &packet[..len]) {
+            Action::Reply => send(stub.reply.as_bytes()),
+            Action::Resume { step } => break step,
+            Action::Detach { reply } => {
+                if reply {
+                    send(stub.reply.as_bytes());
+                }
+                stub.detach();
+                break false;
+            }
+        }
+    };
+
+    stub.resume(frame, step);
+    OWNER.store(NO_CPU, Ordering::Release);
+    true
+}
+
+// ============================================================================
+// Stub State
+// ============================================================================
+
+/// What to do after a command
+enum Action {
+    /// Send the reply and wait for the next command
+    Reply,
+
+    /// Continue, or step one instruction
+    Resume { step: bool },
+
+    /// Stop debugging and continue
+    Detach { reply: bool },
+}
+
+/// A break instruction the stub wrote over kernel code
+#[derive(Clone, Copy)]
+struct Breakpoint {
+    addr: u64,
+
+    /// Bytes replaced, 0 for an unused slot
+    len: usize,
+
+    /// Original contents
+    saved: [u8; 4],
+}
+
+impl Breakpoint {
+    const EMPTY: Self = Self { addr: 0, len: 0, saved: [0; 4] };
+
+    /// Write `insn` at `addr`, keeping what it replaces
+    fn plant(addr: u64, insn: &[u8]) -> Option<Self> {
+        let mut bp = Self { addr, len: insn.len(), saved: [0; 4] };
+        (read_memory(addr, &mut bp.saved[..bp.len]) && write_memory(addr, insn)).then_some(bp)
+    }
+
+    /// Put the original instruction back
+    fn lift(&mut self) {
+        if self.len != 0 {
+            write_memory(self.addr, &self.saved[..self.len]);
+        }
+        *self = Self::EMPTY;
+    }
+}
+
+struct Stub {
+    /// Outgoing packet
+    reply: Reply,
+
+    /// Scratch for memory reads and writes
+    mem: [u8; PACKET_SIZE / 2],
+
+    /// Software breakpoints inserted by GDB
+    breakpoints: [Breakpoint; MAX_BREAKPOINTS],
+
+    /// Temporary breakpoints standing in for a single step
+    step: [Breakpoint; 2],
+
+    /// Hardware breakpoint addresses by slot
+    hw: [Option<u64>; MAX_HW_BREAKPOINTS],
+
+    /// GDB resumed the kernel and expects a stop reply
+    running: bool,
+}
+
+impl Stub {
+    const fn new() -> Self {
+        Self {
+            reply: Reply::new(),
+            mem: [0; PACKET_SIZE / 2],
+            breakpoints: [Breakpoint::EMPTY; MAX_BREAKPOINTS],
+            step: [Breakpoint::EMPTY; 2],
+            hw: [None; MAX_HW_BREAKPOINTS],
+            running: false,
+        }
+    }
+
+    /// Whether GDB has a software breakpoint at `addr`
+    fn is_breakpoint(&self, addr: u64) -> bool {
+        self.breakpoints.iter().any(|bp| bp.len != 0 && bp.addr == addr)
+    }
+
+    /// Undo resume state and point the frame at the stop location
+    fn stop(&mut self, frame: &mut Frame) {
+        // Lift in reverse in case both step targets were the same address
+        arch::set_step(frame, false);
+        self.step.iter_mut().rev().for_each(Breakpoint::lift);
+
+        // Report a trap on one of GDB's breakpoints at the breakpoint,
+        // which matters where the trap leaves the PC past it
+        let pc = arch::pc(frame).wrapping_sub(arch::BREAK_PC_ADJUST);
+        if arch::BREAK_PC_ADJUST != 0 && self.is_breakpoint(pc) {
+            arch::set_pc(frame, pc);
+        }
+    }
+
+    /// Get the frame ready to continue or step
+    fn resume(&mut self, frame: &mut Frame, step: bool) {
+        // Skip a break instruction compiled into the kernel, like the one
+        // `init` stops at. GDB's own breakpoints are out of the way while
+        // it has control, so any still in place are ones it just inserted.
+        let mut insn = [0u8; 4];
+        let pc = arch::pc(frame);
+        if !self.is_breakpoint(pc) && read_memory(pc, &mut insn) {
+            let len = arch::break_len(&insn);
+            if len != 0 {
+                arch::set_pc(frame, pc + len as u64);
+            }
+        }
+
+        if step {
+            if arch::HW_STEP {
+                arch::set_step(frame, true);
+            } else if read_memory(arch::pc(frame), &mut insn) {
+                let brk = arch::break_insn(arch::STEP_BREAK_KIND).unwrap_or(&[]);
+                for (slot, target) in self.step.iter_mut().zip(arch::step_targets(frame, &insn)) {
+                    if let Some(addr) = target {
+                        *slot = Breakpoint::plant(addr, brk).unwrap_or(Breakpoint::EMPTY);
+                    }
+                }
+            }
+        }
+
+        self.running = true;
+        arch::resume(frame);
+    }
+
+    /// Remove everything the stub put in place
+    fn detach(&mut self) {
+        self.breakpoints.iter_mut().for_each(Breakpoint::lift);
+        for slot in 0..MAX_HW_BREAKPOINTS {
+            if self.hw[slot].take().is_some() {
+                arch::set_hw_breakpoint(slot, None);
+            }
+        }
+        self.running = false;
+    }
+
+    /// Handle one packet, leaving any reply in `self.reply`
+    fn command(&mut self, frame: &mut Frame, signal: u8, packet: &[u8]) -> Action {
+        let Some((&kind, args)) = packet.split_first() else {
+            return Action::Reply;
+        };
+
+        match kind {
+            b'?' => self.stop_reply(signal),
+            b'g' => {
+                let mut regs = [0u8; arch::REGS_LEN];
+                arch::read_registers(frame, &mut regs);
+                self.reply.push_hex(&regs);
+            }
+            b'G' => {
+                let mut regs = [0u8; arch::REGS_LEN];
+                arch::read_registers(frame, &mut regs);
+                let ok = decode_hex(args, &mut regs).is_some();
+                if ok {
+                    arch::write_registers(frame, &regs);
+                }
+                self.reply.status(ok);
+            }
+            b'p' => {
+                let mut regs = [0u8; arch::REGS_LEN];
+                arch::read_registers(frame, &mut regs);
+                match parse_hex(args).and_then(register_range) {
+                    Some(range) => self.reply.push_hex(&regs[range]),
+                    None => self.reply.push_str("E01"),
+                }
+            }
+            b'P' => {
+                let mut regs = [0u8; arch::REGS_LEN];
+                arch::read_registers(frame, &mut regs);
+                let ok = split(args, b'=')
+                    .and_then(|(n, value)| Some((register_range(parse_hex(n)?)?, value)))
+                    .and_then(|(range, value)| decode_hex(value, &mut regs[range.clone()]).filter(|&len| len == range.len()))
+                    .is_some();
+                if ok {
+                    arch::write_registers(frame, &regs);
+                }
+                self.reply.status(ok);
+            }
+            b'm' => match parse_addr_len(args) {
+                Some((addr, len)) => {
+                    let len = len.min(self.mem.len());
+                    if read_memory(addr, &mut self.mem[..len]) {
+                        self.reply.push_hex(&self.mem[..len]);
+                    } else {
+                        self.reply.push_str("E14");
+                    }
+                }
+                None => self.reply.push_str("E01"),
+            },
+            b'M' => {
+                let ok = split(args, b':')
+                    .and_then(|(range, data)| Some((parse_addr_len(range)?, data)))
+                    .filter(|&((_, len), data)| len <= self.mem.len() && data.len() == len * 2)
+                    .and_then(|((addr, len), data)| {
+                        decode_hex(data, &mut self.mem[..len])?;
+                        write_memory(addr, &self.mem[..len]).then_some(())
+                    })
+                    .is_some();
+                self.reply.status(ok);
+            }
+            b'c' | b's' => {
+                if let Some(addr) = parse_hex(args) {
+                    arch::set_pc(frame, addr);
+                }
+                return Action::Resume { step: kind == b's' };
+            }
+            b'D' => {
+                self.reply.push_str("OK");
+                return Action::Detach { reply: true };
+            }
+            b'k' => return Action::Detach { reply: false },
+            b'H' => {
+                // Only the stopped thread has registers to select
+                let ok = args.len() > 1 && match &args[1..] {
+                    b"0" | b"-1" => true,
+                    tid => parse_hex(tid) == Some(stopped_thread()),
+                };
+                self.reply.status(ok);
+            }
+            b'T' => {
+                let ok = parse_hex(args).is_some_and(thread_exists);
+                self.reply.status(ok);
+            }
+            b'Z' | b'z' => self.breakpoint_command(kind == b'Z', args),
+            b'q' => self.query(args),
+            _ => {}
+        }
+
+        Action::Reply
+    }
+
+    /// `T` stop reply naming the stopped thread
+    fn stop_reply(&mut self, signal: u8) {
+        let _ = write!(self.reply, "T{:02x}thread:{:x};", signal, stopped_thread());
+    }
+
+    /// `Z`/`z`: insert or remove a breakpoint
+    fn breakpoint_command(&mut self, insert: bool, args: &[u8]) {
+        let mut fields = args.split(|&c| c == b',');
+        let (Some(kind), Some(addr), Some(size)) = (
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+        ) else {
+            self.reply.push_str("E01");
+            return;
+        };
+
+        let ok = match (kind, insert) {
+            (0, true) => self.insert_breakpoint(addr, size),
+            (0, false) => self.remove_breakpoint(addr),
+            (1, true) => self.insert_hw_breakpoint(addr),
+            (1, false) => self.remove_hw_breakpoint(addr),
+            // Watchpoints aren't supported; an empty reply tells GDB so
+            _ => return,
+        };
+        self.reply.status(ok);
+    }
+
+    fn insert_breakpoint(&mut self, addr: u64, kind: u64) -> bool {
+        if self.is_breakpoint(addr) {
+            return true;
+        }
+        let Some(insn) = arch::break_insn(kind) else {
+            return false;
+        };
+        let Some(slot) = self.breakpoints.iter_mut().find(|bp| bp.len == 0) else {
+            return false;
+        };
+        match Breakpoint::plant(addr, insn) {
+            Some(bp) => {
+                *slot = bp;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_breakpoint(&mut self, addr: u64) -> bool {
+        match self.breakpoints.iter_mut().find(|bp| bp.len != 0 && bp.addr == addr) {
+            Some(bp) => {
+                bp.lift();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert_hw_breakpoint(&mut self, addr: u64) -> bool {
+        let slots = arch::hw_breakpoint_slots().min(MAX_HW_BREAKPOINTS);
+        if self.hw[..slots].contains(&Some(addr)) {
+            return true;
+        }
+        match self.hw[..slots].iter().position(Option::is_none) {
+            Some(slot) => {
+                self.hw[slot] = Some(addr);
+                arch::set_hw_breakpoint(slot, Some(addr));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_hw_breakpoint(&mut self, addr: u64) -> bool {
+        match self.hw.iter().position(|&hw| hw == Some(addr)) {
+            Some(slot) => {
+                self.hw[slot] = None;
+                arch::set_hw_breakpoint(slot, None);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `q` queries
+    fn query(&mut self, args: &[u8]) {
+        if args.starts_with(b"Supported") {
+            let _ = write!(self.reply, "PacketSize={:x}", PACKET_SIZE);
+        } else if args == b"Attached" {
+            // Detaching leaves the kernel running rather than killing it
+            self.reply.push_str("1");
+        } else if args == b"C" {
+            let _ = write!(self.reply, "QC{:x}", stopped_thread());
+        } else if args == b"fThreadInfo" {
+            let stopped = stopped_thread();
+            let reply = &mut self.reply;
+            let _ = write!(reply, "m{:x}", stopped);
+            thread::try_for_each_thread(|t| {
+                // Leave room for the longest ID
+                if t.tid != stopped && reply.len + 17 < PACKET_SIZE {
+                    let _ = write!(reply, ",{:x}", t.tid);
+                }
+            });
+        } else if args == b"sThreadInfo" {
+            self.reply.push_str("l");
+        } else if let Some(tid) = args.strip_prefix(b"ThreadExtraInfo,").and_then(parse_hex) {
+            describe_thread(tid, &mut HexWriter(&mut self.reply));
+        }
+    }
+}
+
+/// Thread the stopped CPU was running
+fn stopped_thread() -> ThreadId {
+    match thread::current_thread_id() {
+        TID_INVALID => BOOT_THREAD,
+        tid => tid,
+    }
+}
+
+fn thread_exists(tid: ThreadId) -> bool {
+    let mut found = tid == stopped_thread();
+    thread::try_for_each_thread(|t| found |= t.tid == tid);
+    found
+}
+
+/// Name and state for `info threads`
+fn describe_thread(tid: ThreadId, w: &mut impl Write) {
+    if tid == BOOT_THREAD {
+        let _ = w.write_str("boot");
+        return;
+    }
+    thread::try_for_each_thread(|t| {
+        if t.tid == tid {
+            let name = t.name.try_lock().and_then(|name| *name).unwrap_or("");
+            let _ = match t.state.try_lock() {
+                Some(state) => write!(w, "{} ({:?})", name, *state),
+                None => write!(w, "{}", name),
+            };
+        }
+    });
+}
+
+/// Byte range of register `n` in the `g` packet
+fn register_range(n: u64) -> Option<core::ops::Range<usize>> {
+    let n = usize::try_from(n).ok()?;
+    let size = *arch::REG_SIZES.get(n)?;
+    let offset: usize = arch::REG_SIZES[..n].iter().sum();
+    Some(offset..offset + size)
+}
+
+// ============================================================================
+// Memory Access
+// ============================================================================
+
+/// Call `f(ptr, offset, len)` for each page-sized piece of `addr..addr+len`,
+/// with `ptr` the piece's physmap alias
+///
+/// Stops at the first unmapped page and returns false.
+fn access_memory(addr: u64, len: usize, mut f: impl FnMut(*mut u8, usize, usize)) -> bool {
+    let mut done = 0;
+    while done < len {
+        let va = addr.wrapping_add(done as u64);
+        let Some(pa) = arch::translate(va) else {
+            return false;
+        };
+        let n = (PAGE_SIZE - (va as usize & (PAGE_SIZE - 1))).min(len - done);
+        f(vm::phys_to_physmap(pa) as *mut u8, done, n);
+        done += n;
+    }
+    true
+}
+
+fn read_memory(addr: u64, buf: &mut [u8]) -> bool {
+    access_memory(addr, buf.len(), |ptr, offset, n| {
+        for i in 0..n {
+            buf[offset + i] = unsafe { ptr.add(i).read_volatile() };
+        }
+    })
+}
+
+/// Write `data` at `addr` if it is all mapped, making the change visible
+/// to instruction fetch
+fn write_memory(addr: u64, data: &[u8]) -> bool {
+    if !access_memory(addr, data.len(), |_, _, _| {}) {
+        return false;
+    }
+    access_memory(addr, data.len(), |ptr, offset, n| {
+        for i in 0..n {
+            unsafe { ptr.add(i).write_volatile(data[offset + i]) };
+        }
+    });
+    arch::sync_icache(addr, data.len());
+    true
+}
+
+// ============================================================================
+// Packets
+// ============================================================================
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Outgoing packet body
+struct Reply {
+    buf: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+impl Reply {
+    const fn new() -> Self {
+        Self { buf: [0; PACKET_SIZE], len: 0 }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Append a byte, dropping it if the packet is full
+    fn push(&mut self, c: u8) {
+        if self.len < PACKET_SIZE {
+            self.buf[self.len] = c;
+            self.len += 1;
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        s.bytes().for_each(|c| self.push(c));
+    }
+
+    fn push_hex(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.push(HEX[(b >> 4) as usize]);
+            self.push(HEX[(b & 0xf) as usize]);
+        }
+    }
+
+    /// `OK` or a generic error
+    fn status(&mut self, ok: bool) {
+        self.push_str(if ok { "OK" } else { "E01" });
+    }
+}
+
+impl Write for Reply {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// Formats text into a reply as hex, as `qThreadExtraInfo` wants
+struct HexWriter<'a>(&'a mut Reply);
+
+impl Write for HexWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.push_hex(s.as_bytes());
+        Ok(())
+    }
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parse a hex number of at most 64 bits
+fn parse_hex(s: &[u8]) -> Option<u64> {
+    if s.is_empty() || s.len() > 16 {
+        return None;
+    }
+    s.iter().try_fold(0u64, |n, &c| Some(n << 4 | hex_digit(c)? as u64))
+}
+
+/// Decode hex pairs into the start of `out`, returning the byte count
+fn decode_hex(s: &[u8], out: &mut [u8]) -> Option<usize> {
+    if s.len() % 2 != 0 || s.len() / 2 > out.len() {
+        return None;
+    }
+    for (i, pair) in s.chunks_exact(2).enumerate() {
+        out[i] = hex_digit(pair[0])? << 4 | hex_digit(pair[1])?;
+    }
+    Some(s.len() / 2)
+}
+
+/// Split at the first `sep`
+fn split(s: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let at = s.iter().position(|&c| c == sep)?;
+    Some((&s[..at], &s[at + 1..]))
+}
+
+/// Parse `addr,len`
+fn parse_addr_len(s: &[u8]) -> Option<(u64, usize)> {
+    let (addr, len) = split(s, b',')?;
+    Some((parse_hex(addr)?, usize::try_from(parse_hex(len)?).ok()?))
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &c| sum.wrapping_add(c))
+}
+
+fn getc() -> u8 {
+    loop {
+        if let Some(c) = platform_pgetc() {
+            return c;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Receive a packet into `buf`, acknowledging it, and return its length
+///
+/// Packets with a bad checksum, or too long for `buf`, are refused and
+/// GDB resends them.
+fn receive(buf: &mut [u8]) -> usize {
+    loop {
+        while getc() != b'$' {}
+
+        let mut len = 0;
+        let mut sum = 0u8;
+        loop {
+            match getc() {
+                // A new packet start abandons the current one
+                b'$' => {
+                    len = 0;
+                    sum = 0;
+                }
+                b'#' => break,
+                c => {
+                    sum = sum.wrapping_add(c);
+                    if len < buf.len() {
+                        buf[len] = c;
+                    }
+                    len += 1;
+                }
+            }
+        }
+
+        let expected = [getc(), getc()];
+        if len <= buf.len() && parse_hex(&expected) == Some(sum as u64) {
+            platform_pputc(b'+');
+            return len;
+        }
+        platform_pputc(b'-');
+    }
+}
+
+/// Send a packet, repeating it until GDB acknowledges it
+fn send(data: &[u8]) {
+    let sum = checksum(data);
+    loop {
+        platform_pputc(b'$');
+        data.iter().for_each(|&c| platform_pputc(c));
+        platform_pputc(b'#');
+        platform_pputc(HEX[(sum >> 4) as usize]);
+        platform_pputc(HEX[(sum & 0xf) as usize]);
+
+        loop {
+            match getc() {
+                b'+' => return,
+                b'-' => break,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// `gdb` console command
+fn cmd_gdb(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    if !enabled() {
+        crate::println!("gdb stub not enabled, boot with kernel.gdb=serial");
+        return -1;
+    }
+    crate::println!("stopping in the gdb stub");
+    arch::breakpoint();
+    0
+}
+
+crate::static_command!("gdb", "stop and wait for the debugger", cmd_gdb);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex(b"0"), Some(0));
+        assert_eq!(parse_hex(b"ffffffff80001000"), Some(0xffff_ffff_8000_1000));
+        assert_eq!(parse_hex(b"DeadBeef"), Some(0xdead_beef));
+        assert_eq!(parse_hex(b""), None);
+        assert_eq!(parse_hex(b"12g"), None);
+        assert_eq!(parse_hex(b"10000000000000000"), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        let mut out = [0u8; 4];
+        assert_eq!(decode_hex(b"01ab", &mut out), Some(2));
+        assert_eq!(&out[..2], &[0x01, 0xab]);
+        assert_eq!(decode_hex(b"0", &mut out), None);
+        assert_eq!(decode_hex(b"0011223344", &mut out), None);
+        assert_eq!(decode_hex(b"zz", &mut out), None);
+    }
+
+    #[test]
+    fn test_parse_addr_len() {
+        assert_eq!(parse_addr_len(b"ffff0000,40"), Some((0xffff_0000, 0x40)));
+        assert_eq!(parse_addr_len(b"ffff0000"), None);
+        assert_eq!(parse_addr_len(b",4"), None);
+    }
+
+    #[test]
+    fn test_checksum() {
+        // From the protocol documentation: $OK#9a
+        assert_eq!(checksum(b"OK"), 0x9a);
+        assert_eq!(checksum(b""), 0);
+    }
+
+    #[test]
+    fn test_reply() {
+        let mut reply = Reply::new();
+        reply.push_hex(&[0x00, 0x7f, 0xff]);
+        assert_eq!(reply.as_bytes(), b"007fff");
+
+        reply.clear();
+        let _ = write!(HexWriter(&mut reply), "ok");
+        assert_eq!(reply.as_bytes(), b"6f6b");
+
+        reply.clear();
+        for _ in 0..PACKET_SIZE + 1 {
+            reply.push(b'x');
+        }
+        assert_eq!(reply.len, PACKET_SIZE);
+    }
+
+    #[test]
+    fn test_register_range() {
+        let total: usize = arch::REG_SIZES.iter().sum();
+        assert_eq!(total, arch::REGS_LEN);
+        assert_eq!(register_range(0), Some(0..arch::REG_SIZES[0]));
+        assert_eq!(register_range(arch::REG_SIZES.len() as u64), None);
+    }
+}
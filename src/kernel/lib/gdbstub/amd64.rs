@@ -0,0 +1,189 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86-64 support for the GDB stub
+//!
+//! Debug registers are per CPU, so hardware breakpoints only fire on the
+//! CPU that was stopped when they were set.
+
+use crate::kernel::arch::amd64::registers::x86_get_cr3;
+use crate::kernel::arch::amd64::X86Iframe;
+use crate::kernel::vm::{self, PAddr};
+
+pub type Frame = X86Iframe;
+
+/// Register sizes in `g` packet order: rax, rbx, rcx, rdx, rsi, rdi, rbp,
+/// rsp, r8-r15, rip, then eflags, cs, ss, ds, es, fs, gs
+pub const REG_SIZES: &[usize] = &[
+    8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
+    4, 4, 4, 4, 4, 4, 4,
+];
+
+pub const REGS_LEN: usize = 17 * 8 + 7 * 4;
+
+/// `int3` leaves rip past itself
+pub const BREAK_PC_ADJUST: u64 = 1;
+
+/// The trap flag steps in hardware
+pub const HW_STEP: bool = true;
+
+/// Unused with hardware step
+pub const STEP_BREAK_KIND: u64 = 1;
+
+/// Flags GDB may change: arithmetic, TF and DF. IF and IOPL stay as the
+/// exception found them.
+const FLAGS_WRITABLE: u64 = 0xdd5;
+
+const FLAGS_TF: u64 = 1 << 8;
+const FLAGS_RF: u64 = 1 << 16;
+
+/// DR6 with no conditions recorded (reserved bits read as 1)
+const DR6_CLEAR: u64 = 0xffff_0ff0;
+
+pub fn read_registers(frame: &Frame, regs: &mut [u8; REGS_LEN]) {
+    let gprs = [
+        frame.rax, frame.rbx, frame.rcx, frame.rdx, frame.rsi, frame.rdi, frame.rbp, frame.rsp,
+        frame.r8, frame.r9, frame.r10, frame.r11, frame.r12, frame.r13, frame.r14, frame.r15,
+        frame.rip,
+    ];
+    for (i, value) in gprs.iter().enumerate() {
+        regs[i * 8..i * 8 + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // The data segment registers are flat and not saved
+    let specials = [frame.rflags, frame.user_cs, frame.user_ss, 0, 0, 0, 0];
+    for (i, value) in specials.iter().enumerate() {
+        let offset = 17 * 8 + i * 4;
+        regs[offset..offset + 4].copy_from_slice(&(*value as u32).to_le_bytes());
+    }
+}
+
+pub fn write_registers(frame: &mut Frame, regs: &[u8; REGS_LEN]) {
+    let value = |offset: usize| u64::from_le_bytes(regs[offset..offset + 8].try_into().unwrap());
+
+    let gprs = [
+        &mut frame.rax, &mut frame.rbx, &mut frame.rcx, &mut frame.rdx,
+        &mut frame.rsi, &mut frame.rdi, &mut frame.rbp, &mut frame.rsp,
+        &mut frame.r8, &mut frame.r9, &mut frame.r10, &mut frame.r11,
+        &mut frame.r12, &mut frame.r13, &mut frame.r14, &mut frame.r15,
+        &mut frame.rip,
+    ];
+    for (i, reg) in gprs.into_iter().enumerate() {
+        *reg = value(i * 8);
+    }
+
+    let rflags = u32::from_le_bytes(regs[17 * 8..17 * 8 + 4].try_into().unwrap()) as u64;
+    frame.rflags = (frame.rflags & !FLAGS_WRITABLE) | (rflags & FLAGS_WRITABLE);
+}
+
+pub fn pc(frame: &Frame) -> u64 {
+    frame.rip
+}
+
+pub fn set_pc(frame: &mut Frame, pc: u64) {
+    frame.rip = pc;
+}
+
+/// Break instruction for a `Z0` of size `kind`
+pub fn break_insn(kind: u64) -> Option<&'static [u8]> {
+    match kind {
+        1 => Some(&[0xcc]),
+        _ => None,
+    }
+}
+
+/// Length of a compiled-in break instruction at the PC
+///
+/// Always 0: the PC is already past an `int3`.
+pub fn break_len(_insn: &[u8; 4]) -> usize {
+    0
+}
+
+pub fn set_step(frame: &mut Frame, step: bool) {
+    if step {
+        frame.rflags |= FLAGS_TF;
+    } else {
+        frame.rflags &= !FLAGS_TF;
+    }
+}
+
+/// Unused with hardware step
+pub fn step_targets(_frame: &Frame, _insn: &[u8; 4]) -> [Option<u64>; 2] {
+    [None, None]
+}
+
+pub fn hw_breakpoint_slots() -> usize {
+    4
+}
+
+/// Point DR`slot` at `addr` as an execute breakpoint, or disable it
+pub fn set_hw_breakpoint(slot: usize, addr: Option<u64>) {
+    unsafe {
+        if let Some(addr) = addr {
+            match slot {
+                0 => core::arch::asm!("mov dr0, {}", in(reg) addr, options(nostack)),
+                1 => core::arch::asm!("mov dr1, {}", in(reg) addr, options(nostack)),
+                2 => core::arch::asm!("mov dr2, {}", in(reg) addr, options(nostack)),
+                3 => core::arch::asm!("mov dr3, {}", in(reg) addr, options(nostack)),
+                _ => return,
+            }
+        }
+
+        // Local enable; R/W 00 (execute) and LEN 00 (one byte)
+        let mut dr7: u64;
+        core::arch::asm!("mov {}, dr7", out(reg) dr7, options(nomem, nostack));
+        dr7 &= !(0xf << (16 + slot * 4));
+        if addr.is_some() {
+            dr7 |= 1 << (slot * 2);
+        } else {
+            dr7 &= !(1 << (slot * 2));
+        }
+        core::arch::asm!("mov dr7, {}", in(reg) dr7, options(nostack));
+    }
+}
+
+pub fn resume(frame: &mut Frame) {
+    // Clear the debug status, and don't refault on an instruction
+    // breakpoint at the resume address
+    unsafe { core::arch::asm!("mov dr6, {}", in(reg) DR6_CLEAR, options(nostack)) };
+    frame.rflags |= FLAGS_RF;
+}
+
+/// Translate a virtual address by walking the current page tables
+pub fn translate(va: u64) -> Option<PAddr> {
+    const PRESENT: u64 = 1 << 0;
+    const LARGE: u64 = 1 << 7;
+    const ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+    let mut table = unsafe { x86_get_cr3() } & ADDR_MASK;
+    for level in (0..4).rev() {
+        let shift = 12 + 9 * level;
+        let index = (va >> shift) & 0x1ff;
+        let entry_addr = vm::phys_to_physmap((table + index * 8) as PAddr) as *const u64;
+        let entry = unsafe { entry_addr.read_volatile() };
+        if entry & PRESENT == 0 {
+            return None;
+        }
+
+        // 1 GiB and 2 MiB pages end the walk early
+        if level == 0 || (level < 3 && entry & LARGE != 0) {
+            let offset_mask = (1u64 << shift) - 1;
+            return Some(((entry & ADDR_MASK & !offset_mask) | (va & offset_mask)) as PAddr);
+        }
+        table = entry & ADDR_MASK;
+    }
+    None
+}
+
+/// Instruction fetch is coherent with stores on x86
+pub fn sync_icache(_addr: u64, _len: usize) {}
+
+/// Trap into the stub
+pub fn breakpoint() {
+    unsafe { core::arch::asm!("int3", options(nomem, nostack)) };
+}
+
+pub fn init() {}
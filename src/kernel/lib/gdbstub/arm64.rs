@@ -0,0 +1,211 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ARM64 support for the GDB stub
+//!
+//! Debug exceptions from EL1 need `MDSCR_EL1.KDE` set and `PSTATE.D`
+//! clear, which [`resume`] arranges through the saved frame. Breakpoint
+//! registers are per CPU, so hardware breakpoints only fire on the CPU
+//! that was stopped when they were set.
+
+use crate::kernel::arch::arm64::arm64_iframe_long;
+use crate::kernel::vm::PAddr;
+
+pub type Frame = arm64_iframe_long;
+
+/// Register sizes in `g` packet order: x0-x30, sp, pc, cpsr
+pub const REG_SIZES: &[usize] = &[
+    8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
+    8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
+    8, 8, 4,
+];
+
+pub const REGS_LEN: usize = 33 * 8 + 4;
+
+/// `brk` leaves the PC on itself
+pub const BREAK_PC_ADJUST: u64 = 0;
+
+/// Software step in hardware
+pub const HW_STEP: bool = true;
+
+/// Unused with hardware step
+pub const STEP_BREAK_KIND: u64 = 4;
+
+const MDSCR_SS: u64 = 1 << 0;
+const MDSCR_KDE: u64 = 1 << 13;
+const MDSCR_MDE: u64 = 1 << 15;
+
+const SPSR_D: u64 = 1 << 9;
+const SPSR_SS: u64 = 1 << 21;
+
+/// Condition flags, the only part of the CPSR GDB may change
+const SPSR_NZCV: u64 = 0xf000_0000;
+
+/// `brk #0`
+const BRK: u32 = 0xd420_0000;
+
+/// Execute breakpoint on any instruction size at EL1
+const DBGBCR_EL1_EXEC: u64 = (0xf << 5) | (0b01 << 1) | 1;
+
+/// Stack pointer when the exception was taken
+///
+/// Exceptions from EL1 push the frame on the interrupted stack, so it is
+/// just above the frame.
+fn sp(frame: &Frame) -> u64 {
+    frame as *const Frame as u64 + core::mem::size_of::<Frame>() as u64
+}
+
+pub fn read_registers(frame: &Frame, regs: &mut [u8; REGS_LEN]) {
+    let values = frame.r.iter().copied().chain([frame.lr, sp(frame), frame.elr]);
+    for (i, value) in values.enumerate() {
+        regs[i * 8..i * 8 + 8].copy_from_slice(&value.to_le_bytes());
+    }
+    regs[33 * 8..].copy_from_slice(&(frame.spsr as u32).to_le_bytes());
+}
+
+/// Write back everything but sp, which would move the frame itself
+pub fn write_registers(frame: &mut Frame, regs: &[u8; REGS_LEN]) {
+    let value = |i: usize| u64::from_le_bytes(regs[i * 8..i * 8 + 8].try_into().unwrap());
+
+    for (i, reg) in frame.r.iter_mut().enumerate() {
+        *reg = value(i);
+    }
+    frame.lr = value(30);
+    frame.elr = value(32);
+
+    let cpsr = u32::from_le_bytes(regs[33 * 8..].try_into().unwrap()) as u64;
+    frame.spsr = (frame.spsr & !SPSR_NZCV) | (cpsr & SPSR_NZCV);
+}
+
+pub fn pc(frame: &Frame) -> u64 {
+    frame.elr
+}
+
+pub fn set_pc(frame: &mut Frame, pc: u64) {
+    frame.elr = pc;
+}
+
+/// Break instruction for a `Z0` of size `kind`
+pub fn break_insn(kind: u64) -> Option<&'static [u8]> {
+    const BRK_BYTES: [u8; 4] = BRK.to_le_bytes();
+    match kind {
+        4 => Some(&BRK_BYTES),
+        _ => None,
+    }
+}
+
+/// Length of a compiled-in `brk` at the PC, whatever its immediate
+pub fn break_len(insn: &[u8; 4]) -> usize {
+    if u32::from_le_bytes(*insn) & 0xffe0_001f == BRK {
+        4
+    } else {
+        0
+    }
+}
+
+pub fn set_step(frame: &mut Frame, step: bool) {
+    if step {
+        frame.mdscr |= MDSCR_SS;
+        frame.spsr |= SPSR_SS;
+    } else {
+        frame.mdscr &= !MDSCR_SS;
+        frame.spsr &= !SPSR_SS;
+    }
+}
+
+/// Unused with hardware step
+pub fn step_targets(_frame: &Frame, _insn: &[u8; 4]) -> [Option<u64>; 2] {
+    [None, None]
+}
+
+/// Breakpoint register pairs, from `ID_AA64DFR0_EL1.BRPs`
+pub fn hw_breakpoint_slots() -> usize {
+    let dfr0: u64;
+    unsafe { core::arch::asm!("mrs {}, id_aa64dfr0_el1", out(reg) dfr0, options(nomem, nostack)) };
+    ((dfr0 >> 12) & 0xf) as usize + 1
+}
+
+/// Point breakpoint pair `slot` at `addr`, or disable it
+pub fn set_hw_breakpoint(slot: usize, addr: Option<u64>) {
+    let (bvr, bcr) = match addr {
+        Some(addr) => (addr, DBGBCR_EL1_EXEC),
+        None => (0, 0),
+    };
+
+    unsafe {
+        match slot {
+            0 => core::arch::asm!("msr dbgbvr0_el1, {}", "msr dbgbcr0_el1, {}", in(reg) bvr, in(reg) bcr),
+            1 => core::arch::asm!("msr dbgbvr1_el1, {}", "msr dbgbcr1_el1, {}", in(reg) bvr, in(reg) bcr),
+            2 => core::arch::asm!("msr dbgbvr2_el1, {}", "msr dbgbcr2_el1, {}", in(reg) bvr, in(reg) bcr),
+            3 => core::arch::asm!("msr dbgbvr3_el1, {}", "msr dbgbcr3_el1, {}", in(reg) bvr, in(reg) bcr),
+            _ => return,
+        }
+        core::arch::asm!("isb", options(nostack));
+    }
+}
+
+/// Enable kernel debug exceptions for the code being resumed
+pub fn resume(frame: &mut Frame) {
+    frame.mdscr |= MDSCR_KDE | MDSCR_MDE;
+    frame.spsr &= !SPSR_D;
+}
+
+/// Translate a virtual address with the MMU's own stage 1 walk
+pub fn translate(va: u64) -> Option<PAddr> {
+    let par: u64;
+    unsafe {
+        core::arch::asm!(
+            "at s1e1r, {va}",
+            "isb",
+            "mrs {par}, par_el1",
+            va = in(reg) va,
+            par = out(reg) par,
+            options(nostack)
+        );
+    }
+
+    // PAR_EL1.F reports a failed walk
+    if par & 1 != 0 {
+        return None;
+    }
+    Some(((par & 0x0000_ffff_ffff_f000) | (va & 0xfff)) as PAddr)
+}
+
+/// Clean the data cache and invalidate the instruction cache over a range
+/// of modified code
+pub fn sync_icache(addr: u64, len: usize) {
+    let ctr: u64;
+    unsafe { core::arch::asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack)) };
+    let dline = 4u64 << ((ctr >> 16) & 0xf);
+    let iline = 4u64 << (ctr & 0xf);
+    let end = addr + len as u64;
+
+    unsafe {
+        let mut line = addr & !(dline - 1);
+        while line < end {
+            core::arch::asm!("dc cvau, {}", in(reg) line, options(nostack));
+            line += dline;
+        }
+        core::arch::asm!("dsb ish", options(nostack));
+
+        let mut line = addr & !(iline - 1);
+        while line < end {
+            core::arch::asm!("ic ivau, {}", in(reg) line, options(nostack));
+            line += iline;
+        }
+        core::arch::asm!("dsb ish", "isb", options(nostack));
+    }
+}
+
+/// Trap into the stub
+pub fn breakpoint() {
+    unsafe { core::arch::asm!("brk #0", options(nomem, nostack)) };
+}
+
+/// Release the OS lock, which otherwise holds off debug exceptions
+pub fn init() {
+    unsafe { core::arch::asm!("msr oslar_el1, xzr", "isb", options(nostack)) };
+}
@@ -0,0 +1,268 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RISC-V support for the GDB stub
+//!
+//! Supervisor mode has no single step and the trigger module is only
+//! reachable from machine mode, so stepping is done with temporary
+//! breakpoints on the next instruction's possible targets and there are
+//! no hardware breakpoints.
+
+use crate::kernel::arch::riscv64::exceptions_c::RiscvIframe;
+use crate::kernel::arch::riscv64::mmu::get_satp;
+use crate::kernel::vm::{self, PAddr};
+
+pub type Frame = RiscvIframe;
+
+/// Register sizes in `g` packet order: x0-x31, pc
+pub const REG_SIZES: &[usize] = &[8; 33];
+
+pub const REGS_LEN: usize = 33 * 8;
+
+/// `ebreak` leaves the PC on itself
+pub const BREAK_PC_ADJUST: u64 = 0;
+
+/// Stepping plants breakpoints
+pub const HW_STEP: bool = false;
+
+/// `c.ebreak`, which fits over any instruction
+pub const STEP_BREAK_KIND: u64 = 2;
+
+const EBREAK: u32 = 0x0010_0073;
+const C_EBREAK: u16 = 0x9002;
+
+/// x1-x31 and pc, which the frame keeps in that order
+fn slots(frame: &Frame) -> &[u64; 32] {
+    // SAFETY: RiscvIframe is repr(C) and starts with ra (x1) through t6
+    // (x31) followed by pc, all u64
+    unsafe { &*(frame as *const Frame as *const [u64; 32]) }
+}
+
+fn slots_mut(frame: &mut Frame) -> &mut [u64; 32] {
+    // SAFETY: as for `slots`
+    unsafe { &mut *(frame as *mut Frame as *mut [u64; 32]) }
+}
+
+pub fn read_registers(frame: &Frame, regs: &mut [u8; REGS_LEN]) {
+    // x0 reads as zero
+    regs[..8].fill(0);
+    for (i, value) in slots(frame).iter().enumerate() {
+        regs[(i + 1) * 8..(i + 2) * 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+pub fn write_registers(frame: &mut Frame, regs: &[u8; REGS_LEN]) {
+    for (i, slot) in slots_mut(frame).iter_mut().enumerate() {
+        *slot = u64::from_le_bytes(regs[(i + 1) * 8..(i + 2) * 8].try_into().unwrap());
+    }
+}
+
+pub fn pc(frame: &Frame) -> u64 {
+    frame.pc
+}
+
+pub fn set_pc(frame: &mut Frame, pc: u64) {
+    frame.pc = pc;
+}
+
+/// Break instruction for a `Z0` of size `kind`
+pub fn break_insn(kind: u64) -> Option<&'static [u8]> {
+    const EBREAK_BYTES: [u8; 4] = EBREAK.to_le_bytes();
+    const C_EBREAK_BYTES: [u8; 2] = C_EBREAK.to_le_bytes();
+    match kind {
+        2 => Some(&C_EBREAK_BYTES),
+        4 => Some(&EBREAK_BYTES),
+        _ => None,
+    }
+}
+
+/// Length of a compiled-in `ebreak` or `c.ebreak` at the PC
+pub fn break_len(insn: &[u8; 4]) -> usize {
+    if u16::from_le_bytes([insn[0], insn[1]]) == C_EBREAK {
+        2
+    } else if u32::from_le_bytes(*insn) == EBREAK {
+        4
+    } else {
+        0
+    }
+}
+
+/// No hardware step; see [`step_targets`]
+pub fn set_step(_frame: &mut Frame, _step: bool) {}
+
+/// Where the instruction at the PC can go next
+pub fn step_targets(frame: &Frame, insn: &[u8; 4]) -> [Option<u64>; 2] {
+    let reg = |n: u32| match n {
+        0 => 0,
+        n => slots(frame)[n as usize - 1],
+    };
+    next_pcs(frame.pc, u32::from_le_bytes(*insn), reg)
+}
+
+/// Decode the control flow of the instruction `insn` at `pc`
+///
+/// Conditional branches have two targets; everything else has one.
+fn next_pcs(pc: u64, insn: u32, reg: impl Fn(u32) -> u64) -> [Option<u64>; 2] {
+    let target = |imm: i64| Some(pc.wrapping_add(imm as u64));
+    let bit = |i: u32| (insn >> i) & 1;
+
+    // Compressed instructions
+    if insn & 3 != 3 {
+        let next = Some(pc + 2);
+        let quadrant = insn & 3;
+        let funct3 = (insn >> 13) & 7;
+
+        return match (quadrant, funct3) {
+            // c.j
+            (1, 0b101) => {
+                let imm = bit(12) << 11 | bit(11) << 4 | bit(10) << 9 | bit(9) << 8 | bit(8) << 10
+                    | bit(7) << 6 | bit(6) << 7 | ((insn >> 3) & 7) << 1 | bit(2) << 5;
+                [target(sign_extend(imm, 12)), None]
+            }
+            // c.beqz, c.bnez
+            (1, 0b110) | (1, 0b111) => {
+                let imm = bit(12) << 8 | ((insn >> 10) & 3) << 3 | ((insn >> 5) & 3) << 6
+                    | ((insn >> 3) & 3) << 1 | bit(2) << 5;
+                [next, target(sign_extend(imm, 9))]
+            }
+            // c.jr, c.jalr (rs2 == 0, rs1 != 0; both zero is c.ebreak)
+            (2, 0b100) if (insn >> 2) & 0x1f == 0 && (insn >> 7) & 0x1f != 0 => {
+                [Some(reg((insn >> 7) & 0x1f) & !1), None]
+            }
+            _ => [next, None],
+        };
+    }
+
+    let next = Some(pc + 4);
+    match insn & 0x7f {
+        // jal
+        0x6f => {
+            let imm = (insn & 0x8000_0000) >> 11 | (insn & 0x000f_f000) | (insn >> 9) & 0x800
+                | (insn >> 20) & 0x7fe;
+            [target(sign_extend(imm, 21)), None]
+        }
+        // jalr
+        0x67 => {
+            let base = reg((insn >> 15) & 0x1f);
+            let imm = sign_extend(insn >> 20, 12);
+            [Some(base.wrapping_add(imm as u64) & !1), None]
+        }
+        // beq, bne, blt, bge, bltu, bgeu
+        0x63 => {
+            let imm = (insn & 0x8000_0000) >> 19 | (insn << 4) & 0x800 | (insn >> 20) & 0x7e0
+                | (insn >> 7) & 0x1e;
+            [next, target(sign_extend(imm, 13))]
+        }
+        _ => [next, None],
+    }
+}
+
+/// Sign-extend the low `bits` bits of `value`
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value as i64) << shift) >> shift
+}
+
+pub fn hw_breakpoint_slots() -> usize {
+    0
+}
+
+pub fn set_hw_breakpoint(_slot: usize, _addr: Option<u64>) {}
+
+pub fn resume(_frame: &mut Frame) {}
+
+/// Translate a virtual address by walking the Sv39 or Sv48 tables in satp
+pub fn translate(va: u64) -> Option<PAddr> {
+    const VALID: u64 = 1 << 0;
+    const READ_EXEC: u64 = (1 << 1) | (1 << 3);
+
+    let satp = get_satp();
+    let levels = match satp >> 60 {
+        0 => return Some(va as PAddr),
+        8 => 3,
+        9 => 4,
+        _ => return None,
+    };
+
+    let mut table = (satp & ((1 << 44) - 1)) << 12;
+    for level in (0..levels).rev() {
+        let shift = 12 + 9 * level;
+        let index = (va >> shift) & 0x1ff;
+        let entry_addr = vm::phys_to_physmap((table + index * 8) as PAddr) as *const u64;
+        let pte = unsafe { entry_addr.read_volatile() };
+        if pte & VALID == 0 {
+            return None;
+        }
+
+        // A leaf at any level; higher levels map superpages
+        if pte & READ_EXEC != 0 {
+            let offset_mask = (1u64 << shift) - 1;
+            return Some(((((pte >> 10) << 12) & !offset_mask) | (va & offset_mask)) as PAddr);
+        }
+        table = (pte >> 10) << 12;
+    }
+    None
+}
+
+pub fn sync_icache(_addr: u64, _len: usize) {
+    unsafe { core::arch::asm!("fence.i", options(nostack)) };
+}
+
+/// Trap into the stub
+pub fn breakpoint() {
+    unsafe { core::arch::asm!("ebreak", options(nomem, nostack)) };
+}
+
+pub fn init() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PC: u64 = 0xffff_ffff_8000_1000;
+
+    fn regs(n: u32) -> u64 {
+        n as u64 * 0x100
+    }
+
+    #[test]
+    fn test_jumps() {
+        // jal x0, 8 and jal x0, -4
+        assert_eq!(next_pcs(PC, 0x0080_006f, regs), [Some(PC + 8), None]);
+        assert_eq!(next_pcs(PC, 0xffdf_f06f, regs), [Some(PC - 4), None]);
+        // jalr x0, 0(ra)
+        assert_eq!(next_pcs(PC, 0x0000_8067, regs), [Some(0x100), None]);
+    }
+
+    #[test]
+    fn test_branches() {
+        // beq x0, x0, 16
+        assert_eq!(next_pcs(PC, 0x0000_0863, regs), [Some(PC + 4), Some(PC + 16)]);
+        // c.beqz a0, 8
+        assert_eq!(next_pcs(PC, 0xc501, regs), [Some(PC + 2), Some(PC + 8)]);
+    }
+
+    #[test]
+    fn test_compressed_jumps() {
+        // c.j 4 and ret (c.jr ra)
+        assert_eq!(next_pcs(PC, 0xa011, regs), [Some(PC + 4), None]);
+        assert_eq!(next_pcs(PC, 0x8082, regs), [Some(0x100), None]);
+    }
+
+    #[test]
+    fn test_straight_line() {
+        // addi a0, a0, 1 and c.nop
+        assert_eq!(next_pcs(PC, 0x0015_0513, regs), [Some(PC + 4), None]);
+        assert_eq!(next_pcs(PC, 0x0001, regs), [Some(PC + 2), None]);
+    }
+
+    #[test]
+    fn test_break_len() {
+        assert_eq!(break_len(&EBREAK.to_le_bytes()), 4);
+        assert_eq!(break_len(&[0x02, 0x90, 0x00, 0x00]), 2);
+        assert_eq!(break_len(&0x0015_0513u32.to_le_bytes()), 0);
+    }
+}
@@ -0,0 +1,435 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Hardware Instruction Trace
+//!
+//! Records the exact control flow of CPUs with their trace hardware:
+//! Intel Processor Trace on x86_64, ETE with TRBE on arm64. The CPU
+//! writes compressed branch packets straight into memory; the kernel
+//! only allocates the buffers, starts and stops the hardware, and hands
+//! the buffers to a userspace decoder as VMOs.
+//!
+//! # Sessions
+//!
+//! A session is allocated, started and stopped any number of times, then
+//! freed. `rx_itrace_control` drives it and `rx_itrace_get_buffer` hands
+//! out a stopped session's buffers; the `itrace` tool uses both.
+//!
+//! - **CPU mode**: one buffer per CPU, tracing everything it runs
+//! - **Thread mode**: one buffer, traced while one thread runs. Tracing
+//!   starts when the thread is switched in and stops when it is switched
+//!   out, keeping the buffer offset, so the thread is traced from its
+//!   next switch-in.
+//!
+//! Buffers are physically contiguous and aligned to their size, as Intel
+//! PT single-range output needs. Intel PT wraps when its buffer is full;
+//! TRBE stops, and the buffer is marked `ITRACE_BUFFER_FULL`.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use crate::kernel::arch::arch_traits::ArchItrace;
+use crate::kernel::mp::{self, MpIpiTarget};
+use crate::kernel::object::vmo::Vmo;
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::thread::{self, ThreadId, TID_INVALID};
+use crate::rustux::types::err::*;
+use crate::rustux::types::{PAddr, Status};
+
+use crate::log_info;
+
+#[cfg(target_arch = "x86_64")]
+type Arch = crate::kernel::arch::amd64::Amd64Arch;
+
+#[cfg(target_arch = "aarch64")]
+type Arch = crate::kernel::arch::arm64::Arm64Arch;
+
+#[cfg(target_arch = "riscv64")]
+type Arch = crate::kernel::arch::riscv64::Riscv64Arch;
+
+/// Trace every CPU
+pub const ITRACE_MODE_CPU: u32 = 0;
+
+/// Trace one thread
+pub const ITRACE_MODE_THREAD: u32 = 1;
+
+/// Trace user code
+pub const ITRACE_FLAG_USER: u32 = 1 << 0;
+
+/// Trace kernel code
+pub const ITRACE_FLAG_KERNEL: u32 = 1 << 1;
+
+/// Tracing stopped because the buffer filled
+pub const ITRACE_BUFFER_FULL: u32 = 1 << 0;
+
+/// `cpu` of the buffer in thread mode
+pub const ITRACE_CPU_THREAD: u32 = u32::MAX;
+
+/// Smallest and largest buffer, in bytes
+pub const ITRACE_MIN_BUFFER_SIZE: u64 = 4096;
+pub const ITRACE_MAX_BUFFER_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A session, as passed to `ALLOC`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ItraceConfig {
+    /// `ITRACE_MODE_*`
+    pub mode: u32,
+
+    /// `ITRACE_FLAG_*`, 0 for both
+    pub flags: u32,
+
+    /// Size of each buffer in bytes, a power of two
+    pub buffer_size: u64,
+
+    /// Thread to trace in thread mode
+    pub thread: u64,
+}
+
+/// A stopped session's buffer, as read by `rx_itrace_get_buffer`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ItraceBufferInfo {
+    /// CPU, or `ITRACE_CPU_THREAD`
+    pub cpu: u32,
+
+    /// `ITRACE_BUFFER_*` bits
+    pub flags: u32,
+
+    /// Size of the buffer in bytes
+    pub size: u64,
+
+    /// Where the trace ends; with Intel PT, where the newest trace ends
+    /// if it wrapped
+    pub offset: u64,
+}
+
+/// Where a session is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionState {
+    Free,
+    Stopped,
+    Running,
+}
+
+/// A session's configuration and buffers
+struct Session {
+    state: SessionState,
+    config: ItraceConfig,
+    buffers: Vec<Arc<Vmo>>,
+}
+
+static SESSION: SpinMutex<Session> = SpinMutex::new(Session {
+    state: SessionState::Free,
+    config: ItraceConfig { mode: ITRACE_MODE_CPU, flags: 0, buffer_size: 0, thread: 0 },
+    buffers: Vec::new(),
+});
+
+/// One buffer, as the context switch and IPI paths see it
+#[repr(align(64))]
+struct Slot {
+    /// Physical address, 0 if there is no buffer
+    base: AtomicU64,
+
+    /// Where the trace ends
+    offset: AtomicU64,
+
+    /// `ITRACE_BUFFER_*` bits
+    flags: AtomicU32,
+
+    /// Whether this CPU is tracing into the slot
+    tracing: AtomicBool,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            base: AtomicU64::new(0),
+            offset: AtomicU64::new(0),
+            flags: AtomicU32::new(0),
+            tracing: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Per-CPU buffers in CPU mode
+static CPU_SLOTS: [Slot; SMP_MAX_CPUS] = [const { Slot::new() }; SMP_MAX_CPUS];
+
+/// The thread's buffer in thread mode
+static THREAD_SLOT: Slot = Slot::new();
+
+/// CPU tracing into `THREAD_SLOT`, if any
+static THREAD_CPU: AtomicU32 = AtomicU32::new(u32::MAX);
+
+/// Whether a session is running, and its mode, thread, flags and size,
+/// readable without the lock
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static MODE: AtomicU32 = AtomicU32::new(ITRACE_MODE_CPU);
+static TARGET: AtomicU64 = AtomicU64::new(TID_INVALID);
+static FLAGS: AtomicU32 = AtomicU32::new(0);
+static SIZE: AtomicU64 = AtomicU64::new(0);
+
+/// Report whether the CPU can trace
+pub fn init() {
+    if Arch::itrace_supported() {
+        log_info!("itrace: hardware trace available");
+    } else {
+        log_info!("itrace: no hardware trace");
+    }
+}
+
+/// Whether the CPU can trace
+pub fn supported() -> bool {
+    Arch::itrace_supported()
+}
+
+/// Check a configuration
+fn validate(config: &ItraceConfig) -> Result<(), Status> {
+    let size = config.buffer_size;
+    if !size.is_power_of_two()
+        || !(ITRACE_MIN_BUFFER_SIZE..=ITRACE_MAX_BUFFER_SIZE).contains(&size)
+        || config.flags & !(ITRACE_FLAG_USER | ITRACE_FLAG_KERNEL) != 0
+    {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    match config.mode {
+        ITRACE_MODE_CPU => Ok(()),
+        ITRACE_MODE_THREAD if config.thread != TID_INVALID => Ok(()),
+        _ => Err(RX_ERR_INVALID_ARGS),
+    }
+}
+
+/// Slot of buffer `index` in `mode`
+fn slot(mode: u32, index: usize) -> &'static Slot {
+    if mode == ITRACE_MODE_THREAD {
+        &THREAD_SLOT
+    } else {
+        &CPU_SLOTS[index]
+    }
+}
+
+/// Allocate a session's buffers
+///
+/// Fails with `BAD_STATE` if a session is already allocated and
+/// `NOT_SUPPORTED` if the CPU can't trace.
+pub fn alloc(config: &ItraceConfig) -> Result<(), Status> {
+    if !supported() {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    validate(config)?;
+    if config.mode == ITRACE_MODE_THREAD && thread::get_thread_by_id(config.thread).is_none() {
+        return Err(RX_ERR_BAD_HANDLE);
+    }
+
+    let mut session = SESSION.lock();
+    if session.state != SessionState::Free {
+        return Err(RX_ERR_BAD_STATE);
+    }
+
+    let count = if config.mode == ITRACE_MODE_THREAD {
+        1
+    } else {
+        (percpu::num_cpus() as usize).clamp(1, SMP_MAX_CPUS)
+    };
+    let size = config.buffer_size as usize;
+    let mut buffers = Vec::with_capacity(count);
+    let mut bases = Vec::with_capacity(count);
+    for _ in 0..count {
+        let vmo = Arc::new(Vmo::create_contiguous(size, size.trailing_zeros() as u8)?);
+        bases.push(vmo.commit_page_paddr(0)?);
+        buffers.push(vmo);
+    }
+    for (index, &base) in bases.iter().enumerate() {
+        let slot = slot(config.mode, index);
+        slot.base.store(base as u64, Ordering::Relaxed);
+        slot.offset.store(0, Ordering::Relaxed);
+        slot.flags.store(0, Ordering::Relaxed);
+    }
+
+    session.config = *config;
+    session.buffers = buffers;
+    session.state = SessionState::Stopped;
+
+    MODE.store(config.mode, Ordering::Relaxed);
+    TARGET.store(if config.mode == ITRACE_MODE_THREAD { config.thread } else { TID_INVALID }, Ordering::Relaxed);
+    let flags = match config.flags {
+        0 => ITRACE_FLAG_USER | ITRACE_FLAG_KERNEL,
+        flags => flags,
+    };
+    FLAGS.store(flags, Ordering::Relaxed);
+    SIZE.store(config.buffer_size, Ordering::Relaxed);
+
+    log_info!("itrace: {} buffers of {} KiB", count, size / 1024);
+    Ok(())
+}
+
+/// Free a stopped session's buffers
+///
+/// Buffers handed to userspace stay alive until their handles are closed.
+pub fn free() -> Result<(), Status> {
+    let mut session = SESSION.lock();
+    match session.state {
+        SessionState::Running => return Err(RX_ERR_BAD_STATE),
+        SessionState::Free => return Ok(()),
+        SessionState::Stopped => {}
+    }
+
+    for index in 0..session.buffers.len() {
+        slot(session.config.mode, index).base.store(0, Ordering::Relaxed);
+    }
+    session.buffers = Vec::new();
+    session.state = SessionState::Free;
+    Ok(())
+}
+
+/// Start tracing into the allocated buffers, from their start
+pub fn start() -> Result<(), Status> {
+    let mut session = SESSION.lock();
+    if session.state != SessionState::Stopped {
+        return Err(RX_ERR_BAD_STATE);
+    }
+
+    for index in 0..session.buffers.len() {
+        let slot = slot(session.config.mode, index);
+        slot.offset.store(0, Ordering::Relaxed);
+        slot.flags.store(0, Ordering::Relaxed);
+    }
+    session.state = SessionState::Running;
+    RUNNING.store(true, Ordering::Release);
+
+    if session.config.mode == ITRACE_MODE_CPU {
+        mp::mp_sync_exec(MpIpiTarget::All, 0, start_task, 0);
+    }
+    log_info!("itrace: started, mode {}", session.config.mode);
+    Ok(())
+}
+
+/// Stop tracing and flush every CPU's trace
+pub fn stop() {
+    let mut session = SESSION.lock();
+    if session.state != SessionState::Running {
+        return;
+    }
+    mp::mp_sync_exec(MpIpiTarget::All, 0, stop_task, 0);
+    RUNNING.store(false, Ordering::Release);
+    session.state = SessionState::Stopped;
+    log_info!("itrace: stopped");
+}
+
+unsafe fn start_task(_context: u64) {
+    if let Some(slot) = CPU_SLOTS.get(percpu::current_cpu_num() as usize) {
+        load(slot);
+    }
+}
+
+unsafe fn stop_task(_context: u64) {
+    let cpu = percpu::current_cpu_num();
+    if MODE.load(Ordering::Relaxed) == ITRACE_MODE_THREAD {
+        if THREAD_CPU.load(Ordering::Relaxed) == cpu {
+            unload(&THREAD_SLOT);
+            THREAD_CPU.store(u32::MAX, Ordering::Relaxed);
+        }
+    } else if let Some(slot) = CPU_SLOTS.get(cpu as usize) {
+        unload(slot);
+    }
+}
+
+/// Start tracing into `slot` on this CPU, unless its buffer is full
+fn load(slot: &Slot) {
+    let base = slot.base.load(Ordering::Relaxed);
+    if base == 0 || slot.flags.load(Ordering::Relaxed) & ITRACE_BUFFER_FULL != 0 {
+        return;
+    }
+    unsafe {
+        Arch::itrace_start(
+            base as PAddr,
+            SIZE.load(Ordering::Relaxed) as usize,
+            slot.offset.load(Ordering::Relaxed) as usize,
+            FLAGS.load(Ordering::Relaxed),
+        );
+    }
+    slot.tracing.store(true, Ordering::Relaxed);
+}
+
+/// Stop tracing into `slot` on this CPU and note where the trace ends
+fn unload(slot: &Slot) {
+    if !slot.tracing.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let (offset, full) = unsafe { Arch::itrace_stop() };
+    slot.offset.store(offset as u64, Ordering::Relaxed);
+    if full {
+        slot.flags.fetch_or(ITRACE_BUFFER_FULL, Ordering::Relaxed);
+    }
+}
+
+/// Move the traced thread's tracing on and off this CPU
+///
+/// Called by the scheduler when it switches from `prev` to `next`.
+#[inline]
+pub fn context_switch(prev: Option<ThreadId>, next: ThreadId) {
+    if !RUNNING.load(Ordering::Relaxed) || MODE.load(Ordering::Relaxed) != ITRACE_MODE_THREAD {
+        return;
+    }
+    let target = TARGET.load(Ordering::Relaxed);
+    if prev == Some(target) {
+        unload(&THREAD_SLOT);
+        THREAD_CPU.store(u32::MAX, Ordering::Relaxed);
+    }
+    if next == target {
+        load(&THREAD_SLOT);
+        THREAD_CPU.store(percpu::current_cpu_num(), Ordering::Relaxed);
+    }
+}
+
+/// Buffer `index` of a stopped session, to hand to userspace
+///
+/// Fails with `BAD_STATE` while tracing or without a session and
+/// `OUT_OF_RANGE` past the last buffer.
+pub fn buffer(index: usize) -> Result<(ItraceBufferInfo, Arc<Vmo>), Status> {
+    let session = SESSION.lock();
+    if session.state != SessionState::Stopped {
+        return Err(RX_ERR_BAD_STATE);
+    }
+    let vmo = session.buffers.get(index).ok_or(RX_ERR_OUT_OF_RANGE)?;
+    let slot = slot(session.config.mode, index);
+    let info = ItraceBufferInfo {
+        cpu: if session.config.mode == ITRACE_MODE_THREAD { ITRACE_CPU_THREAD } else { index as u32 },
+        flags: slot.flags.load(Ordering::Relaxed),
+        size: session.config.buffer_size,
+        offset: slot.offset.load(Ordering::Relaxed),
+    };
+    Ok((info, vmo.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(core::mem::size_of::<ItraceConfig>(), 24);
+        assert_eq!(core::mem::size_of::<ItraceBufferInfo>(), 24);
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ItraceConfig { buffer_size: 1 << 20, ..Default::default() };
+        assert_eq!(validate(&config), Ok(()));
+
+        assert_eq!(validate(&ItraceConfig { buffer_size: 3 << 20, ..config }), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&ItraceConfig { buffer_size: 1024, ..config }), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&ItraceConfig { buffer_size: 1 << 27, ..config }), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&ItraceConfig { flags: 1 << 5, ..config }), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&ItraceConfig { mode: ITRACE_MODE_THREAD, ..config }), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(
+            validate(&ItraceConfig { mode: ITRACE_MODE_THREAD, thread: 7, ..config }),
+            Ok(())
+        );
+    }
+}
@@ -6,348 +6,394 @@
 
 //! Kernel Trace
 //!
-//! This module provides kernel tracing functionality for debugging and performance analysis.
+//! Binary trace of kernel events: context switches, syscalls, interrupts,
+//! VM events and userspace probes. Read out with `rx_ktrace_read` and
+//! turned into Chrome trace-event JSON by the `ktrace` tool.
+//!
+//! # Design
+//!
+//! Every CPU owns a ring of fixed-size [`KtraceRecord`]s. A writer claims a
+//! slot with one relaxed `fetch_add` on its CPU's head and fills it in, so
+//! tracing never takes a lock and an interrupt that lands mid-write simply
+//! claims the next slot. When a ring is full the oldest records are
+//! overwritten.
+//!
+//! Events belong to groups, and a runtime group mask decides which are
+//! recorded. A disabled probe costs one relaxed load.
+//!
+//! Readers see the rings oldest record first, one CPU after another. The
+//! snapshot is only consistent while tracing is stopped; `rx_ktrace_read`
+//! does not stop it, so tools stop tracing before reading.
+//!
+//! # Command Line
+//!
+//! - `ktrace.bufsize=<KiB>` - Ring size per CPU (default 256, 0 disables)
+//! - `ktrace.grpmask=<mask>` - Groups to trace from boot (default none)
 
-#![no_std]
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use crate::kernel::cmdline;
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::timer;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Status;
+
+use crate::{log_info, log_warn};
+
+/// Kernel trace tag
+#[allow(non_camel_case_types)]
+pub type ktrace_tag_t = u32;
+
+/// Default ring size per CPU, in KiB
+const DEFAULT_BUFSIZE_KB: u32 = 256;
+
+/// Trace groups
+pub const KTRACE_GRP_META: u32 = 0x0001;
+pub const KTRACE_GRP_LIFECYCLE: u32 = 0x0002;
+pub const KTRACE_GRP_SCHEDULER: u32 = 0x0004;
+pub const KTRACE_GRP_SYSCALL: u32 = 0x0008;
+pub const KTRACE_GRP_IRQ: u32 = 0x0010;
+pub const KTRACE_GRP_IPC: u32 = 0x0020;
+pub const KTRACE_GRP_PROBE: u32 = 0x0040;
+pub const KTRACE_GRP_VM: u32 = 0x0080;
+pub const KTRACE_GRP_ALL: u32 = 0x00ff;
+
+/// Build a tag from a group and an event number
+///
+/// The group occupies the high half so the enable check is a shift and a
+/// mask.
+pub const fn ktrace_tag(group: u32, event: u32) -> ktrace_tag_t {
+    (group << 16) | (event & 0xffff)
+}
 
-extern crate alloc;
+/// Group of a tag
+#[inline]
+pub const fn ktrace_tag_group(tag: ktrace_tag_t) -> u32 {
+    tag >> 16
+}
 
-use alloc::collections::LinkedList;
-use alloc::format;
-use alloc::string::String;
-use alloc::sync::Arc;
-use alloc::vec::Vec;
-use core::sync::atomic::{AtomicI32, AtomicU32, AtomicU64, AtomicU8, Ordering};
-use spin::Mutex;
-
-use crate::rustux::types::*;
-
-/// Default trace buffer size
-const DEFAULT_KTRACE_BUFSIZE: usize = 1024 * 1024; // 1MB
-
-/// Trace group mask bits
-pub const KTRACE_GRP_ALL: u32 = 0x0000ffff;
-pub const KTRACE_GRP_META: u32 = 0x00000001;
-pub const KTRACE_GRP_LIFECYCLE: u32 = 0x00000002;
-pub const KTRACE_GRP_SCHEDULER: u32 = 0x00000004;
-pub const KTRACE_GRP_SYSCALL: u32 = 0x00000008;
-pub const KTRACE_GRP_PLATFORM: u32 = 0x00000010;
-pub const KTRACE_GRP_IPC: u32 = 0x00000020;
-pub const KTRACE_GRP_PROBE: u32 = 0x00000040;
-
-/// Trace actions
-pub const KTRACE_ACTION_START: u32 = 0;
-pub const KTRACE_ACTION_STOP: u32 = 1;
-pub const KTRACE_ACTION_REWIND: u32 = 2;
-
-/// Convert group number to mask
+/// Event number of a tag
 #[inline]
-pub const fn ktrace_grp_to_mask(grp: u32) -> u32 {
-    1u32 << grp
+pub const fn ktrace_tag_event(tag: ktrace_tag_t) -> u32 {
+    tag & 0xffff
 }
 
-/// Trace tag names
-pub const TAG_SYSCALL_NAME: u32 = 0x100;
-pub const TAG_PROBE_NAME: u32 = 0x101;
-pub const TAG_THREAD_NAME: u32 = 0x102;
-pub const TAG_PROCESS_NAME: u32 = 0x103;
+/// Probe name: `id` is the probe, `args` hold up to 16 bytes of the name
+pub const TAG_PROBE_NAME: u32 = ktrace_tag(KTRACE_GRP_META, 1);
+
+/// Context switch: `args` are the old and new thread ids
+pub const TAG_CONTEXT_SWITCH: u32 = ktrace_tag(KTRACE_GRP_SCHEDULER, 1);
 
-/// Syscall info structure
+/// Syscall entry: `id` is the syscall number, `args` its first two arguments
+pub const TAG_SYSCALL_ENTER: u32 = ktrace_tag(KTRACE_GRP_SYSCALL, 1);
+
+/// Syscall exit: `id` is the syscall number, `args[0]` the return value
+pub const TAG_SYSCALL_EXIT: u32 = ktrace_tag(KTRACE_GRP_SYSCALL, 2);
+
+/// Interrupt entry: `id` is the vector
+pub const TAG_IRQ_ENTER: u32 = ktrace_tag(KTRACE_GRP_IRQ, 1);
+
+/// Interrupt exit: `id` is the vector
+pub const TAG_IRQ_EXIT: u32 = ktrace_tag(KTRACE_GRP_IRQ, 2);
+
+/// Page fault: `args` are the faulting address and the fault flags
+pub const TAG_PAGE_FAULT: u32 = ktrace_tag(KTRACE_GRP_VM, 1);
+
+/// VMAR map: `args` are the address and length
+pub const TAG_VMAR_MAP: u32 = ktrace_tag(KTRACE_GRP_VM, 2);
+
+/// VMAR unmap: `args` are the address and length
+pub const TAG_VMAR_UNMAP: u32 = ktrace_tag(KTRACE_GRP_VM, 3);
+
+/// Userspace probe: `id` is the probe, `args` are the probe's arguments
+pub const TAG_PROBE: u32 = ktrace_tag(KTRACE_GRP_PROBE, 0);
+
+/// Highest probe id
+pub const KTRACE_MAX_PROBE: u16 = 0x7ff;
+
+/// Most probes that can be registered
+const MAX_PROBES: usize = 64;
+
+/// Longest probe name kept in a name record
+pub const KTRACE_PROBE_NAME_LEN: usize = 16;
+
+/// One trace record, as read out by `rx_ktrace_read`
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct KtraceSyscallInfo {
-    pub id: u32,
-    pub nargs: u32,
-    pub name: &'static str,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KtraceRecord {
+    /// Monotonic time in nanoseconds
+    pub ts: u64,
+
+    /// Group and event
+    pub tag: u32,
+
+    /// CPU that wrote the record
+    pub cpu: u16,
+
+    /// Event-specific id: syscall number, vector or probe
+    pub id: u16,
+
+    /// Event-specific arguments
+    pub args: [u64; 2],
 }
 
-/// Probe info structure
-#[repr(C)]
-#[derive(Debug)]
-pub struct KtraceProbeInfo {
-    pub name: &'static str,
-    pub num: u32,
-    pub next: Option<*mut KtraceProbeInfo>,
+impl KtraceRecord {
+    /// Size of a record in bytes
+    pub const SIZE: usize = core::mem::size_of::<Self>();
 }
 
-/// KTrace state
-struct KtraceState {
-    /// Current write offset
-    offset: AtomicU32,
-    /// Group mask (0 = disabled)
-    grpmask: AtomicU32,
-    /// Total buffer size
-    bufsize: u32,
-    /// Offset where tracing was stopped
-    marker: AtomicU32,
-    /// Trace buffer
-    buffer: Vec<u8>,
+/// One CPU's ring, on its own cache line
+#[repr(align(64))]
+struct Ring {
+    /// Records, `CAPACITY` of them, or null before `init`
+    records: AtomicPtr<KtraceRecord>,
+
+    /// Records ever written; the next slot is `head % CAPACITY`
+    head: AtomicU64,
 }
 
-impl KtraceState {
-    fn new(bufsize: usize) -> Self {
+impl Ring {
+    const fn new() -> Self {
         Self {
-            offset: AtomicU32::new(0),
-            grpmask: AtomicU32::new(0),
-            bufsize: bufsize as u32,
-            marker: AtomicU32::new(0),
-            buffer: vec![0; bufsize],
+            records: AtomicPtr::new(core::ptr::null_mut()),
+            head: AtomicU64::new(0),
         }
     }
 }
 
-/// Global ktrace state
-static KTRACE_STATE: Mutex<Option<KtraceState>> = Mutex::new(None);
+static RINGS: [Ring; SMP_MAX_CPUS] = [const { Ring::new() }; SMP_MAX_CPUS];
 
-/// Probe list
-static PROBE_LIST: Mutex<LinkedList<Arc<KtraceProbeInfo>>> = Mutex::new(LinkedList::new());
+/// Records per ring
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
 
-/// Probe number counter
-static PROBE_NUMBER: AtomicU32 = AtomicU32::new(1);
+/// Groups being traced, 0 when stopped
+static GRPMASK: AtomicU32 = AtomicU32::new(0);
 
-/// Initialize ktrace with the specified buffer size
-pub fn ktrace_init(bufsize: usize) {
-    let mut state = KTRACE_STATE.lock();
+/// Registered probe names, indexed by probe id - 1
+static PROBES: SpinMutex<Vec<[u8; KTRACE_PROBE_NAME_LEN]>> = SpinMutex::new(Vec::new());
 
-    if state.is_some() {
-        return; // Already initialized
+/// Allocate the per-CPU rings and apply the boot group mask
+pub fn init() {
+    let kb = cmdline::cmdline_get_uint32("ktrace.bufsize", DEFAULT_BUFSIZE_KB) as usize;
+    let capacity = kb * 1024 / KtraceRecord::SIZE;
+    if capacity == 0 {
+        log_info!("ktrace: disabled");
+        return;
     }
 
-    *state = Some(KtraceState::new(bufsize));
-
-    println!("KTRACE: initialized with {} byte buffer", bufsize);
-}
-
-/// Read ktrace data to user buffer
-///
-/// # Arguments
-///
-/// * `ptr` - User buffer to read to (null = query size)
-/// * `off` - Offset in trace buffer
-/// * `len` - Length to read
-///
-/// # Returns
-///
-/// Number of bytes read, or buffer size if ptr is null
-pub fn ktrace_read_user(ptr: Option<&mut [u8]>, off: u32, len: usize) -> Result<usize, i32> {
-    let state = KTRACE_STATE.lock();
-
-    let ks = state.as_ref().ok_or(-1)?;
-
-    // Determine max offset
-    let max = if ks.marker.load(Ordering::Acquire) != 0 {
-        ks.marker.load(Ordering::Acquire)
-    } else {
-        let offset = ks.offset.load(Ordering::Acquire);
-        if offset > ks.bufsize {
-            ks.bufsize
-        } else {
-            offset
-        }
-    };
-
-    // Null read = query for size
-    if ptr.is_none() {
-        return Ok(max as usize);
+    let cpus = (percpu::num_cpus() as usize).clamp(1, SMP_MAX_CPUS);
+    for ring in &RINGS[..cpus] {
+        let mut records = Vec::with_capacity(capacity);
+        records.resize(capacity, KtraceRecord { ts: 0, tag: 0, cpu: 0, id: 0, args: [0; 2] });
+        ring.records.store(records.leak().as_mut_ptr(), Ordering::Release);
     }
+    CAPACITY.store(capacity, Ordering::Release);
 
-    let ptr = ptr.unwrap();
+    log_info!("ktrace: {} records per CPU on {} CPUs", capacity, cpus);
 
-    // Constrain read to available buffer
-    if off >= max {
-        return Ok(0);
+    let mask = cmdline::cmdline_get_uint32("ktrace.grpmask", 0);
+    if mask != 0 {
+        start(mask);
     }
+}
 
-    let available = (max - off) as usize;
-    let to_read = len.min(available);
+/// Whether any group in `group` is being traced
+#[inline]
+pub fn enabled(group: u32) -> bool {
+    GRPMASK.load(Ordering::Relaxed) & group != 0
+}
 
-    // Copy to user buffer
-    // TODO: Implement arch_copy_to_user
-    let buffer_slice = &ks.buffer[off as usize..(off as usize + to_read)];
-    ptr[..to_read].copy_from_slice(buffer_slice);
+/// Groups being traced
+pub fn grpmask() -> u32 {
+    GRPMASK.load(Ordering::Relaxed)
+}
 
-    Ok(to_read)
+/// Record an event if its group is enabled
+#[inline]
+pub fn write(tag: ktrace_tag_t, id: u16, arg0: u64, arg1: u64) {
+    if enabled(ktrace_tag_group(tag)) {
+        write_record(tag, id, [arg0, arg1]);
+    }
 }
 
-/// Control ktrace
-///
-/// # Arguments
-///
-/// * `action` - Action to perform (START, STOP, REWIND)
-/// * `options` - Options for the action
-pub fn ktrace_control(action: u32, options: u32) -> Result<(), i32> {
-    let mut state = KTRACE_STATE.lock();
-
-    let ks = state.as_mut().ok_or(-1)?;
-
-    match action {
-        KTRACE_ACTION_START => {
-            let mask = if options != 0 {
-                ktrace_grp_to_mask(options)
-            } else {
-                KTRACE_GRP_ALL
-            };
+/// Claim a slot on this CPU's ring and fill it in
+fn write_record(tag: ktrace_tag_t, id: u16, args: [u64; 2]) {
+    write_on(percpu::current_cpu_num() as usize, tag, id, args);
+}
 
-            ks.marker.store(0, Ordering::Release);
-            ks.grpmask.store(mask, Ordering::Release);
+/// Claim a slot on `cpu`'s ring and fill it in
+fn write_on(cpu: usize, tag: ktrace_tag_t, id: u16, args: [u64; 2]) {
+    if cpu >= SMP_MAX_CPUS {
+        return;
+    }
 
-            // Report live processes and threads
-            // TODO: Implement ktrace_report_live_processes and ktrace_report_live_threads
+    let ring = &RINGS[cpu];
+    let records = ring.records.load(Ordering::Acquire);
+    if records.is_null() {
+        return;
+    }
 
-            println!("KTRACE: started");
-        }
-        KTRACE_ACTION_STOP => {
-            ks.grpmask.store(0, Ordering::Release);
-
-            let offset = ks.offset.load(Ordering::Acquire);
-            let marker = if offset > ks.bufsize {
-                ks.bufsize
-            } else {
-                offset
-            };
-            ks.marker.store(marker, Ordering::Release);
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    let seq = ring.head.fetch_add(1, Ordering::Relaxed);
+    let record = KtraceRecord {
+        ts: timer::current_time(),
+        tag,
+        cpu: cpu as u16,
+        id,
+        args,
+    };
 
-            println!("KTRACE: stopped at {}", marker);
-        }
-        KTRACE_ACTION_REWIND => {
-            ks.offset.store(0, Ordering::Release);
-            ks.marker.store(0, Ordering::Release);
+    unsafe { core::ptr::write_volatile(records.add((seq % capacity as u64) as usize), record) };
+}
 
-            println!("KTRACE: rewound");
-        }
-        _ => {
-            return Err(-2); // Invalid action
+/// Start tracing the groups in `mask`
+///
+/// Re-emits the probe names so a trace taken after a rewind can still
+/// name its probes.
+pub fn start(mask: u32) {
+    GRPMASK.store(mask & KTRACE_GRP_ALL, Ordering::Release);
+
+    if enabled(KTRACE_GRP_META) {
+        for (i, name) in PROBES.lock().iter().enumerate() {
+            write_name(i as u16 + 1, name);
         }
     }
-
-    Ok(())
+    log_info!("ktrace: started, groups {:#x}", mask);
 }
 
-/// Write a ktrace record
-///
-/// # Arguments
-///
-/// * `tag` - Trace tag
-/// * `args` - Trace arguments (up to 4)
-pub fn ktrace_write(tag: u32, args: [u64; 4]) {
-    // TODO: Implement trace writing
-    let _ = (tag, args);
+/// Stop tracing
+pub fn stop() {
+    GRPMASK.store(0, Ordering::Release);
+    log_info!("ktrace: stopped");
 }
 
-/// Write a name record
+/// Discard every record
 ///
-/// # Arguments
-///
-/// * `tag` - Name tag
-/// * `id` - ID
-/// * `arg` - Additional argument
-/// * `name` - Name string
-pub fn ktrace_name_etc(tag: u32, id: u32, arg: u32, name: &str) {
-    // TODO: Implement name recording
-    let _ = (tag, id, arg, name);
+/// Records written concurrently may survive; rewind while stopped.
+pub fn rewind() {
+    for ring in RINGS.iter() {
+        ring.head.store(0, Ordering::Release);
+    }
+    log_info!("ktrace: rewound");
 }
 
-/// Add a probe
-///
-/// # Arguments
+/// Register a probe called `name` and return its id
 ///
-/// * `name` - Probe name
-///
-/// # Returns
-///
-/// Probe number
-pub fn ktrace_add_probe(name: &'static str) -> u32 {
-    let num = PROBE_NUMBER.fetch_add(1, Ordering::AcqRel);
+/// Registering a name twice returns the same id.
+pub fn add_probe(name: &[u8]) -> Result<u16, Status> {
+    let packed = pack_name(name);
+    let mut probes = PROBES.lock();
 
-    let info = Arc::new(KtraceProbeInfo {
-        name,
-        num,
-        next: None,
-    });
+    if let Some(i) = probes.iter().position(|p| *p == packed) {
+        return Ok(i as u16 + 1);
+    }
+    if probes.len() >= MAX_PROBES {
+        log_warn!("ktrace: probe table full");
+        return Err(RX_ERR_NO_RESOURCES);
+    }
 
-    PROBE_LIST.lock().push_back(info.clone());
+    probes.push(packed);
+    let id = probes.len() as u16;
+    drop(probes);
 
-    // Report the probe
-    ktrace_name_etc(TAG_PROBE_NAME, num, 0, name);
+    if enabled(KTRACE_GRP_META) {
+        write_name(id, &packed);
+    }
+    Ok(id)
+}
 
-    num
+/// Record the name of probe `id`
+fn write_name(id: u16, name: &[u8; KTRACE_PROBE_NAME_LEN]) {
+    let (lo, hi) = name.split_at(8);
+    write_record(TAG_PROBE_NAME, id, [
+        u64::from_le_bytes(lo.try_into().unwrap()),
+        u64::from_le_bytes(hi.try_into().unwrap()),
+    ]);
 }
 
-/// Find a probe by name
-pub fn ktrace_find_probe(name: &str) -> Option<Arc<KtraceProbeInfo>> {
-    let list = PROBE_LIST.lock();
-    for probe in list.iter() {
-        if probe.name == name {
-            return Some(probe.clone());
-        }
-    }
-    None
+/// Truncate or NUL-pad a name to fit a name record
+fn pack_name(name: &[u8]) -> [u8; KTRACE_PROBE_NAME_LEN] {
+    let mut packed = [0u8; KTRACE_PROBE_NAME_LEN];
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len()).min(KTRACE_PROBE_NAME_LEN);
+    packed[..len].copy_from_slice(&name[..len]);
+    packed
 }
 
-/// Report all probes
-pub fn ktrace_report_probes() {
-    let list = PROBE_LIST.lock();
-    for probe in list.iter() {
-        ktrace_name_etc(TAG_PROBE_NAME, probe.num, 0, probe.name);
-    }
+/// Oldest retained record and number of retained records of a ring that
+/// has had `head` records written to `capacity` slots
+fn window(head: u64, capacity: usize) -> (u64, usize) {
+    let len = head.min(capacity as u64);
+    (head - len, len as usize)
 }
 
-/// Get current trace state
-pub fn ktrace_get_state() -> (u32, u32, u32) {
-    let state = KTRACE_STATE.lock();
-
-    if let Some(ks) = state.as_ref() {
-        (
-            ks.offset.load(Ordering::Acquire),
-            ks.grpmask.load(Ordering::Acquire),
-            ks.marker.load(Ordering::Acquire),
-        )
-    } else {
-        (0, 0, 0)
-    }
+/// Bytes of trace available to `read`
+pub fn size() -> usize {
+    let capacity = CAPACITY.load(Ordering::Acquire);
+    RINGS
+        .iter()
+        .map(|ring| window(ring.head.load(Ordering::Acquire), capacity).1)
+        .sum::<usize>()
+        * KtraceRecord::SIZE
 }
 
-/// Check if tracing is enabled for a group
-pub fn ktrace_enabled(grp: u32) -> bool {
-    let state = KTRACE_STATE.lock();
+/// Copy trace records starting at byte `offset` into `buf`
+///
+/// The trace is each CPU's ring in turn, oldest record first. `offset`
+/// and the length of `buf` are rounded down to whole records. Returns the
+/// number of bytes copied, 0 at the end of the trace.
+pub fn read(offset: usize, buf: &mut [u8]) -> usize {
+    let capacity = CAPACITY.load(Ordering::Acquire);
+    let mut skip = offset / KtraceRecord::SIZE;
+    let mut copied = 0;
+
+    for ring in RINGS.iter() {
+        let records = ring.records.load(Ordering::Acquire);
+        if records.is_null() {
+            continue;
+        }
 
-    if let Some(ks) = state.as_ref() {
-        let mask = ks.grpmask.load(Ordering::Acquire);
-        mask != 0 && (mask & ktrace_grp_to_mask(grp)) != 0
-    } else {
-        false
-    }
-}
+        let (start, len) = window(ring.head.load(Ordering::Acquire), capacity);
+        if skip >= len {
+            skip -= len;
+            continue;
+        }
 
-/// Quick trace (only if tracing is enabled)
-#[inline]
-pub fn ktrace_quick(tag: u32, arg0: u32, arg1: u32) {
-    if ktrace_enabled(tag >> 16) {
-        ktrace_write(tag, [arg0 as u64, arg1 as u64, 0, 0]);
+        for seq in start + skip as u64..start + len as u64 {
+            let out = match buf.get_mut(copied..copied + KtraceRecord::SIZE) {
+                Some(out) => out,
+                None => return copied,
+            };
+            let record = unsafe {
+                core::ptr::read_volatile(records.add((seq % capacity as u64) as usize))
+            };
+            out.copy_from_slice(unsafe {
+                core::slice::from_raw_parts(&record as *const KtraceRecord as *const u8, KtraceRecord::SIZE)
+            });
+            copied += KtraceRecord::SIZE;
+        }
+        skip = 0;
     }
+    copied
 }
 
-/// Kernel trace probe with 64-bit argument (stub)
+/// Kernel trace probe with 64-bit argument
 #[inline]
-pub fn ktrace_probe64(_tag: u32, _arg: u64) {
-    // Stub - no-op for now
+pub fn ktrace_probe64(tag: ktrace_tag_t, arg: u64) {
+    write(tag, 0, arg, 0);
 }
 
-/// Kernel trace probe with no arguments (stub)
+/// Kernel trace probe with no arguments
 #[inline]
-pub fn ktrace_probe0(_tag: u32) {
-    // Stub - no-op for now
+pub fn ktrace_probe0(tag: ktrace_tag_t) {
+    write(tag, 0, 0, 0);
 }
 
-/// Kernel trace probe with two arguments (stub)
+/// Kernel trace probe with two arguments
 #[inline]
-pub fn ktrace_probe2(_tag: u32, _arg1: u64, _arg2: u64) {
-    // Stub - no-op for now
-}
-
-/// Initialize kernel tracing
-pub fn init() {
-    ktrace_init(DEFAULT_KTRACE_BUFSIZE);
+pub fn ktrace_probe2(tag: ktrace_tag_t, arg1: u64, arg2: u64) {
+    write(tag, 0, arg1, arg2);
 }
 
 #[cfg(test)]
@@ -355,50 +401,86 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_ktrace_init() {
-        ktrace_init(1024);
-
-        let (offset, grpmask, marker) = ktrace_get_state();
-        assert_eq!(offset, 0);
-        assert_eq!(grpmask, 0);
-        assert_eq!(marker, 0);
+    fn test_record_layout() {
+        assert_eq!(KtraceRecord::SIZE, 32);
     }
 
     #[test]
-    fn test_ktrace_control() {
-        ktrace_init(1024);
-
-        // Start tracing
-        ktrace_control(KTRACE_ACTION_START, KTRACE_GRP_ALL).unwrap();
-
-        let (_, grpmask, _) = ktrace_get_state();
-        assert_eq!(grpmask, KTRACE_GRP_ALL);
-
-        // Stop tracing
-        ktrace_control(KTRACE_ACTION_STOP, 0).unwrap();
-
-        let (_, grpmask, marker) = ktrace_get_state();
-        assert_eq!(grpmask, 0);
-        assert_eq!(marker, 0);
+    fn test_tags() {
+        assert_eq!(ktrace_tag_group(TAG_SYSCALL_EXIT), KTRACE_GRP_SYSCALL);
+        assert_eq!(ktrace_tag_event(TAG_SYSCALL_EXIT), 2);
+        assert_eq!(ktrace_tag_group(TAG_PAGE_FAULT), KTRACE_GRP_VM);
+        assert_eq!(KTRACE_GRP_ALL & KTRACE_GRP_VM, KTRACE_GRP_VM);
     }
 
     #[test]
-    fn test_ktrace_enabled() {
-        ktrace_init(1024);
+    fn test_window() {
+        assert_eq!(window(0, 8), (0, 0));
+        assert_eq!(window(5, 8), (0, 5));
+        assert_eq!(window(8, 8), (0, 8));
+        assert_eq!(window(13, 8), (5, 8));
+    }
 
-        assert!(!ktrace_enabled(KTRACE_GRP_META));
+    #[test]
+    fn test_pack_name() {
+        assert_eq!(&pack_name(b"probe\0junk")[..6], b"probe\0");
+        assert_eq!(pack_name(b"a_rather_long_probe_name"), *b"a_rather_long_pr");
+    }
 
-        ktrace_control(KTRACE_ACTION_START, KTRACE_GRP_META).unwrap();
-        assert!(ktrace_enabled(KTRACE_GRP_META));
+    fn records(buf: &[u8]) -> Vec<KtraceRecord> {
+        buf.chunks_exact(KtraceRecord::SIZE)
+            .map(|chunk| unsafe { core::ptr::read_unaligned(chunk.as_ptr() as *const KtraceRecord) })
+            .collect()
     }
 
     #[test]
-    fn test_probe() {
-        let num = ktrace_add_probe("test_probe");
-        assert!(num > 0);
+    fn test_rings() {
+        let empty = KtraceRecord { ts: 0, tag: 0, cpu: 0, id: 0, args: [0; 2] };
+        for ring in &RINGS[..2] {
+            ring.records.store(alloc::vec![empty; 4].leak().as_mut_ptr(), Ordering::Release);
+        }
+        CAPACITY.store(4, Ordering::Release);
+        rewind();
+
+        // Only the started groups are traced
+        assert!(!enabled(KTRACE_GRP_SYSCALL));
+        start(KTRACE_GRP_SYSCALL | 0x8000_0000);
+        assert_eq!(grpmask(), KTRACE_GRP_SYSCALL);
+        assert!(enabled(KTRACE_GRP_SYSCALL));
+        assert!(!enabled(KTRACE_GRP_IRQ));
+        stop();
+        assert!(!enabled(KTRACE_GRP_SYSCALL));
+
+        // A full ring keeps its newest records
+        for i in 0..6 {
+            write_on(0, TAG_SYSCALL_ENTER, i, [i as u64, 0]);
+        }
+        write_on(1, TAG_IRQ_ENTER, 32, [0, 0]);
+        assert_eq!(size(), 5 * KtraceRecord::SIZE);
+
+        // CPU 0's ring first, oldest record first
+        let mut buf = [0u8; 8 * KtraceRecord::SIZE];
+        assert_eq!(read(0, &mut buf), 5 * KtraceRecord::SIZE);
+        let trace = records(&buf[..5 * KtraceRecord::SIZE]);
+        assert_eq!(trace.iter().map(|r| r.id).collect::<Vec<_>>(), [2, 3, 4, 5, 32]);
+        assert_eq!(trace[0].args, [2, 0]);
+        assert_eq!((trace[4].cpu, trace[4].tag), (1, TAG_IRQ_ENTER));
+
+        // Offsets and lengths round down to whole records
+        let mut one = [0u8; KtraceRecord::SIZE + 5];
+        assert_eq!(read(3 * KtraceRecord::SIZE + 1, &mut one), KtraceRecord::SIZE);
+        assert_eq!(records(&one)[0].id, 5);
+        assert_eq!(read(5 * KtraceRecord::SIZE, &mut one), 0);
+
+        rewind();
+        assert_eq!(size(), 0);
+        assert_eq!(read(0, &mut buf), 0);
+    }
 
-        let probe = ktrace_find_probe("test_probe");
-        assert!(probe.is_some());
-        assert_eq!(probe.unwrap().name, "test_probe");
+    #[test]
+    fn test_add_probe() {
+        let id = add_probe(b"test_add_probe").unwrap();
+        assert_eq!(add_probe(b"test_add_probe\0ignored"), Ok(id));
+        assert_ne!(add_probe(b"test_add_probe2").unwrap(), id);
     }
 }
@@ -4,442 +4,496 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT
 
-//! Lock Dependency Tracking
+//! Lock Dependency Validator
 //!
-//! This module provides lockdep (lock dependency) tracking to detect
-//! potential deadlocks and circular lock dependencies at runtime.
-
-#![no_std]
-
-extern crate alloc;
-
-use alloc::collections::BTreeMap;
-use alloc::format;
-use alloc::string::String;
-use alloc::vec::Vec;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
-use spin::Mutex;
+//! Catches lock order inversions before they deadlock. Every lock belongs
+//! to a class; whenever a thread takes a lock while holding others, each
+//! held class gets an edge to the new one. An edge that closes a cycle
+//! means two code paths take the same locks in opposite orders. It is
+//! reported with this thread's backtrace and the backtraces recorded when
+//! the edges of the opposite order were first seen.
+//!
+//! # Design
+//!
+//! - **Classes**: A lock's [`Class`] is the site that created it, so the
+//!   same lock field of every instance of a struct is one class. Classes
+//!   are registered on first acquisition, up to [`MAX_LOCK_CLASSES`].
+//! - **Held locks**: Tracked per thread, by the current thread ID in
+//!   per-CPU data. Locks taken by an interrupt handler count as nested in
+//!   whatever the interrupted thread holds.
+//! - **Edges**: A bitmap per class. The backtrace of the first acquisition
+//!   that added an edge is kept for reports, which are made once per edge.
+//! - **Try-locks** never wait, so they are recorded as held but add no
+//!   edges. Nesting locks of one class, such as a parent and a child VMAR,
+//!   isn't checked.
+//! - **Re-entry**: The validator runs with interrupts disabled under a raw
+//!   spinlock of its own. Locks it takes itself, such as the heap's, are
+//!   not tracked.
+//!
+//! # Configuration
+//!
+//! Only built with the `lockdep` feature in debug builds. Otherwise
+//! [`Class`] is empty and its hooks are inline no-ops, so release kernels
+//! carry none of this.
+//!
+//! The `lockdep` shell command lists classes and their edges.
 
-use crate::rustux::types::*;
+use crate::kernel::sync::lockstat::LockKind;
+use core::panic::Location;
 
-/// Maximum number of lock classes
-const MAX_LOCK_CLASSES: usize = 256;
+/// Largest number of lock classes
+pub const MAX_LOCK_CLASSES: usize = 256;
 
-/// Lock class ID type
+/// Lock class ID; 0 is unregistered
 pub type LockClassId = u16;
 
-/// Lock order counter
-static LOCK_ORDER_COUNTER: AtomicU64 = AtomicU64::new(0);
-
-/// Acquired lock entry
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct AcquiredLockEntry {
-    /// Lock class ID
-    pub id: LockClassId,
-    /// Lock order (when this lock was acquired)
-    pub order: u64,
-}
+/// Whether lock dependencies are validated in this build
+pub const ENABLED: bool = cfg!(all(feature = "lockdep", debug_assertions));
 
-impl AcquiredLockEntry {
-    /// Create a new acquired lock entry
-    pub fn new(id: LockClassId) -> Self {
-        Self {
-            id,
-            order: LOCK_ORDER_COUNTER.fetch_add(1, Ordering::AcqRel),
-        }
-    }
+/// Lock order inversions reported since boot
+pub fn inversions() -> i64 {
+    #[cfg(all(feature = "lockdep", debug_assertions))]
+    return enabled::inversions();
 
-    /// Get the lock class ID
-    pub fn id(&self) -> LockClassId {
-        self.id
-    }
-
-    /// Get the lock order
-    pub fn order(&self) -> u64 {
-        self.order
-    }
+    #[cfg(not(all(feature = "lockdep", debug_assertions)))]
+    0
 }
 
-/// Lock result types
-#[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum LockResult {
-    /// Lock acquisition is valid
-    Ok,
-    /// Lock order violation detected
-    OrderViolation,
-    /// Circular dependency detected
-    CircularDependency,
-    /// Invalid lock class
-    InvalidLockClass,
-}
+/// ============================================================================
+/// Class
+/// ============================================================================
 
-impl LockResult {
-    /// Convert lock result to string
-    pub fn to_string(self) -> &'static str {
-        match self {
-            LockResult::Ok => "Ok",
-            LockResult::OrderViolation => "Order violation",
-            LockResult::CircularDependency => "Circular dependency",
-            LockResult::InvalidLockClass => "Invalid lock class",
-        }
-    }
-}
+/// A lock's class (lockdep builds)
+#[cfg(all(feature = "lockdep", debug_assertions))]
+pub struct Class {
+    /// Where the lock was created
+    site: &'static Location<'static>,
 
-/// Lock class state
-pub struct LockClassState {
-    /// Lock class name
-    pub name: String,
-    /// Lock class ID
-    pub id: LockClassId,
-    /// Set of locks this lock depends on (must acquire after)
-    pub dependency_set: Vec<LockClassId>,
-    /// Connected set pointer (for cycle detection)
-    pub connected_set: Option<usize>,
+    /// Registered ID, or 0
+    id: core::sync::atomic::AtomicU16,
 }
 
-impl LockClassState {
-    /// Create a new lock class state
-    pub fn new(name: &str, id: LockClassId) -> Self {
-        Self {
-            name: name.to_string(),
-            id,
-            dependency_set: Vec::new(),
-            connected_set: None,
-        }
-    }
+/// A lock's class (other builds: empty)
+#[cfg(not(all(feature = "lockdep", debug_assertions)))]
+pub struct Class;
 
-    /// Get the lock class name
-    pub fn name(&self) -> &str {
-        &self.name
+#[cfg(all(feature = "lockdep", debug_assertions))]
+impl Class {
+    /// The class of locks created at the caller
+    #[track_caller]
+    pub const fn new() -> Self {
+        Self { site: Location::caller(), id: core::sync::atomic::AtomicU16::new(0) }
     }
 
-    /// Get the dependency set
-    pub fn dependency_set(&self) -> &[LockClassId] {
-        &self.dependency_set
+    /// The lock at `lock` was taken; `try_lock` if it couldn't have waited
+    #[inline]
+    pub fn acquire(&self, lock: usize, kind: LockKind, try_lock: bool) {
+        enabled::acquire(self, lock, kind, try_lock);
     }
 
-    /// Get the connected set index
-    pub fn connected_set(&self) -> Option<usize> {
-        self.connected_set
+    /// The lock at `lock` was released
+    #[inline]
+    pub fn release(&self, lock: usize) {
+        enabled::release(lock);
     }
 }
 
-/// Thread-local lock state
-#[repr(C)]
-#[derive(Debug)]
-pub struct ThreadLockState {
-    /// List of currently held locks
-    acquired_locks: Vec<AcquiredLockEntry>,
-    /// Current lock depth
-    lock_depth: usize,
-}
-
-impl ThreadLockState {
-    /// Create a new thread lock state
-    pub fn new() -> Self {
-        Self {
-            acquired_locks: Vec::new(),
-            lock_depth: 0,
-        }
+#[cfg(not(all(feature = "lockdep", debug_assertions)))]
+impl Class {
+    /// The class of locks created at the caller
+    #[track_caller]
+    pub const fn new() -> Self {
+        Self
     }
 
-    /// Add an acquired lock
-    pub fn push_lock(&mut self, entry: AcquiredLockEntry) {
-        self.acquired_locks.push(entry);
-        self.lock_depth += 1;
-    }
+    /// The lock at `lock` was taken; `try_lock` if it couldn't have waited
+    #[inline]
+    pub fn acquire(&self, _lock: usize, _kind: LockKind, _try_lock: bool) {}
 
-    /// Remove the most recently acquired lock
-    pub fn pop_lock(&mut self) -> Option<AcquiredLockEntry> {
-        if self.lock_depth > 0 {
-            self.lock_depth -= 1;
-            self.acquired_locks.pop()
-        } else {
-            None
-        }
-    }
+    /// The lock at `lock` was released
+    #[inline]
+    pub fn release(&self, _lock: usize) {}
+}
 
-    /// Get the list of acquired locks
-    pub fn acquired_locks(&self) -> &[AcquiredLockEntry] {
-        &self.acquired_locks
+/// ============================================================================
+/// Validator
+/// ============================================================================
+
+#[cfg(all(feature = "lockdep", debug_assertions))]
+mod enabled {
+    use super::{Class, LockClassId, LockKind, MAX_LOCK_CLASSES};
+    use crate::kernel::arch;
+    use crate::kernel::lib::backtrace::Backtrace;
+    use crate::kernel::percpu;
+    use crate::kernel::thread::ThreadId;
+    use alloc::collections::{BTreeMap, VecDeque};
+    use alloc::vec::Vec;
+    use core::panic::Location;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    // Import KCOUNTER macro at crate level
+    use crate::KCOUNTER;
+
+    // Import logging macros
+    use crate::log_error;
+
+    KCOUNTER!(LOCKDEP_INVERSIONS, "kernel.lockdep.inversions");
+    KCOUNTER!(LOCKDEP_CLASSES_FULL, "kernel.lockdep.classes_full");
+
+    const WORDS: usize = MAX_LOCK_CLASSES / 64;
+
+    /// A registered class
+    #[derive(Clone, Copy)]
+    pub struct ClassInfo {
+        /// Where its locks are created
+        pub site: &'static Location<'static>,
+
+        /// Kind of its locks
+        pub kind: LockKind,
     }
 
-    /// Get the current lock depth
-    pub fn lock_depth(&self) -> usize {
-        self.lock_depth
+    /// First sighting of an edge
+    struct Edge {
+        from: LockClassId,
+        to: LockClassId,
+        backtrace: Backtrace,
     }
 
-    /// Find a lock entry by ID
-    pub fn find_lock(&self, id: LockClassId) -> Option<&AcquiredLockEntry> {
-        self.acquired_locks.iter().find(|e| e.id() == id)
+    /// A lock held by a thread
+    #[derive(Clone, Copy)]
+    struct Held {
+        class: LockClassId,
+        lock: usize,
     }
-}
-
-/// Global lock dependency tracking state
-static LOCK_CLASSES: Mutex<BTreeMap<LockClassId, LockClassState>> = Mutex::new(BTreeMap::new());
-
-/// Next lock class ID
-static NEXT_LOCK_CLASS_ID: AtomicUsize = AtomicUsize::new(0);
 
-/// Loop detection enabled flag
-static LOOP_DETECTION_ENABLED: AtomicBool = AtomicBool::new(false);
+    /// Taking `taking` while holding `held` closed a cycle
+    pub struct Inversion {
+        /// Class being taken
+        pub taking: LockClassId,
 
-/// Initialize lock dependency tracking
-pub fn init() {
-    println!("LockDep: Lock dependency tracking initialized");
-}
+        /// Class held
+        pub held: LockClassId,
 
-/// Register a new lock class
-///
-/// # Arguments
-///
-/// * `name` - Lock class name
-///
-/// # Returns
-///
-/// Lock class ID
-pub fn register_lock_class(name: &str) -> LockClassId {
-    let id = NEXT_LOCK_CLASS_ID.fetch_add(1, Ordering::AcqRel) as LockClassId;
-
-    if id >= MAX_LOCK_CLASSES as u16 {
-        panic!("LockDep: Too many lock classes registered");
+        /// Existing path from `taking` to `held`
+        pub path: Vec<LockClassId>,
     }
 
-    let state = LockClassState::new(name, id);
-
-    let mut classes = LOCK_CLASSES.lock();
-    classes.insert(id, state);
-
-    println!("LockDep: Registered lock class '{}' with ID {}", name, id);
-
-    id
-}
-
-/// Validate lock acquisition
-///
-/// # Arguments
-///
-/// * `state` - Thread lock state
-/// * `id` - Lock class ID to acquire
-///
-/// # Returns
-///
-/// LockResult indicating if acquisition is valid
-pub fn validate_lock_acquire(state: &ThreadLockState, id: LockClassId) -> LockResult {
-    let classes = LOCK_CLASSES.lock();
-
-    // Check if lock class exists
-    if !classes.contains_key(&id) {
-        return LockResult::InvalidLockClass;
+    /// The dependency graph
+    pub struct Graph {
+        classes: Vec<ClassInfo>,
+        after: [[u64; WORDS]; MAX_LOCK_CLASSES],
+        edges: Vec<Edge>,
+        held: BTreeMap<ThreadId, Vec<Held>>,
     }
 
-    // Check for circular dependencies
-    for entry in state.acquired_locks() {
-        if let Some(class) = classes.get(&entry.id()) {
-            // Check if the new lock depends on any held lock
-            // This would create a circular dependency
-            if class.dependency_set().contains(&id) {
-                return LockResult::CircularDependency;
+    impl Graph {
+        pub const fn new() -> Self {
+            Self {
+                classes: Vec::new(),
+                after: [[0; WORDS]; MAX_LOCK_CLASSES],
+                edges: Vec::new(),
+                held: BTreeMap::new(),
             }
         }
-    }
-
-    LockResult::Ok
-}
 
-/// Record a lock dependency
-///
-/// # Arguments
-///
-/// * `held_id` - Currently held lock class ID
-/// * `new_id` - Newly acquired lock class ID
-pub fn record_lock_dependency(held_id: LockClassId, new_id: LockClassId) {
-    let mut classes = LOCK_CLASSES.lock();
-
-    if let Some(class) = classes.get_mut(&held_id) {
-        // Add the dependency if not already present
-        if !class.dependency_set.contains(&new_id) {
-            class.dependency_set.push(new_id);
-
-            // Trigger loop detection if enabled
-            if LOOP_DETECTION_ENABLED.load(Ordering::Acquire) {
-                drop(classes);
-                trigger_loop_detection();
+        /// The class of `kind` locks created at `site`, registering it if
+        /// new, or `None` if the table is full
+        pub fn register(&mut self, site: &'static Location<'static>, kind: LockKind) -> Option<LockClassId> {
+            if let Some(i) = self.classes.iter().position(|c| *c.site == *site && c.kind == kind) {
+                return Some(i as LockClassId + 1);
             }
+            if self.classes.len() >= MAX_LOCK_CLASSES {
+                return None;
+            }
+            self.classes.push(ClassInfo { site, kind });
+            Some(self.classes.len() as LockClassId)
         }
-    }
-}
-
-/// Enable loop detection
-pub fn enable_loop_detection() {
-    LOOP_DETECTION_ENABLED.store(true, Ordering::Release);
-    println!("LockDep: Loop detection enabled");
-}
 
-/// Trigger loop detection pass
-pub fn trigger_loop_detection() {
-    // TODO: Implement loop detection algorithm
-    println!("LockDep: Loop detection triggered");
-}
+        pub fn class(&self, id: LockClassId) -> ClassInfo {
+            self.classes[id as usize - 1]
+        }
 
-/// Perform a loop detection pass
-pub fn loop_detection_pass() {
-    let classes = LOCK_CLASSES.lock();
+        fn has_edge(&self, from: LockClassId, to: LockClassId) -> bool {
+            let (word, bit) = ((to as usize - 1) / 64, (to as usize - 1) % 64);
+            self.after[from as usize - 1][word] & (1 << bit) != 0
+        }
 
-    println!("LockDep: Performing loop detection pass...");
+        fn add_edge(&mut self, from: LockClassId, to: LockClassId, backtrace: Backtrace) {
+            let (word, bit) = ((to as usize - 1) / 64, (to as usize - 1) % 64);
+            self.after[from as usize - 1][word] |= 1 << bit;
+            self.edges.push(Edge { from, to, backtrace });
+        }
 
-    // TODO: Implement proper cycle detection using DFS or Union-Find
-    // For now, just check for trivial cycles
+        /// Classes from `from` to `to` along existing edges, if connected
+        fn path(&self, from: LockClassId, to: LockClassId) -> Option<Vec<LockClassId>> {
+            let mut previous = [0 as LockClassId; MAX_LOCK_CLASSES];
+            let mut queue = VecDeque::from([from]);
+            previous[from as usize - 1] = from;
+            while let Some(class) = queue.pop_front() {
+                if class == to {
+                    let mut path = Vec::from([to]);
+                    let mut at = to;
+                    while at != from {
+                        at = previous[at as usize - 1];
+                        path.push(at);
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                for next in 1..=self.classes.len() as LockClassId {
+                    if previous[next as usize - 1] == 0 && self.has_edge(class, next) {
+                        previous[next as usize - 1] = class;
+                        queue.push_back(next);
+                    }
+                }
+            }
+            None
+        }
 
-    for (id, class) in classes.iter() {
-        for dep_id in class.dependency_set() {
-            if let Some(dep_class) = classes.get(dep_id) {
-                // Check if the dependency depends on us (direct cycle)
-                if dep_class.dependency_set().contains(id) {
-                    system_circular_lock_dependency_detected(*id);
-                    return;
+        /// Record `thread` taking `lock` of `class`
+        ///
+        /// Returns the cycles the new edges close. `capture` is called for
+        /// the backtrace of each new edge.
+        pub fn acquire(
+            &mut self,
+            thread: ThreadId,
+            class: LockClassId,
+            lock: usize,
+            try_lock: bool,
+            capture: fn() -> Backtrace,
+        ) -> Vec<Inversion> {
+            let mut inversions = Vec::new();
+            let held: Vec<LockClassId> =
+                self.held.get(&thread).map_or(Vec::new(), |held| held.iter().map(|h| h.class).collect());
+
+            if !try_lock {
+                for &held_class in &held {
+                    if held_class == class || self.has_edge(held_class, class) {
+                        continue;
+                    }
+                    if let Some(path) = self.path(class, held_class) {
+                        inversions.push(Inversion { taking: class, held: held_class, path });
+                    }
+                    self.add_edge(held_class, class, capture());
                 }
             }
+
+            self.held.entry(thread).or_default().push(Held { class, lock });
+            inversions
         }
-    }
 
-    println!("LockDep: Loop detection complete - no cycles found");
-}
+        /// Record `thread` releasing `lock`
+        pub fn release(&mut self, thread: ThreadId, lock: usize) {
+            if let Some(held) = self.held.get_mut(&thread) {
+                if let Some(i) = held.iter().rposition(|h| h.lock == lock) {
+                    held.remove(i);
+                }
+                if held.is_empty() {
+                    self.held.remove(&thread);
+                }
+            }
+        }
 
-/// Get the current thread's lock state
-///
-/// # Returns
-///
-/// Thread lock state for the current thread
-pub fn get_thread_lock_state() -> ThreadLockState {
-    // TODO: Get from thread-local storage
-    ThreadLockState::new()
-}
+        fn edge(&self, from: LockClassId, to: LockClassId) -> Option<&Edge> {
+            self.edges.iter().find(|e| e.from == from && e.to == to)
+        }
 
-/// Initialize thread lock state
-pub fn init_thread_lock_state() -> ThreadLockState {
-    ThreadLockState::new()
-}
+        fn describe(&self, id: LockClassId) -> alloc::string::String {
+            let class = self.class(id);
+            let kind = match class.kind {
+                LockKind::Spin => "spin",
+                LockKind::Mutex => "mutex",
+                LockKind::RwLock => "rwlock",
+            };
+            alloc::format!("{} lock created at {} (class {})", kind, class.site, id)
+        }
 
-/// Dump lock class state for debugging
-pub fn dump_lock_class_state() {
-    let classes = LOCK_CLASSES.lock();
+        fn report(&self, inversion: &Inversion) {
+            LOCKDEP_INVERSIONS.add(1);
+            log_error!("lockdep: lock order inversion");
+            log_error!("  taking {}", self.describe(inversion.taking));
+            log_error!("  while holding {}", self.describe(inversion.held));
+            log_error!("  here:");
+            Backtrace::capture().print();
+            log_error!("  but the opposite order was seen:");
+            for pair in inversion.path.windows(2) {
+                log_error!("  {} then {}", self.describe(pair[0]), self.describe(pair[1]));
+                if let Some(edge) = self.edge(pair[0], pair[1]) {
+                    edge.backtrace.print();
+                }
+            }
+        }
 
-    println!("Lock class states:");
-    for (_id, class) in classes.iter() {
-        println!("  {} {{", class.name());
-        for dep_id in class.dependency_set() {
-            if let Some(dep_class) = classes.get(dep_id) {
-                println!("    {}", dep_class.name());
+        /// Print every class and the classes taken while holding it
+        pub fn dump(&self) {
+            for id in 1..=self.classes.len() as LockClassId {
+                crate::println!("{}", self.describe(id));
+                for to in 1..=self.classes.len() as LockClassId {
+                    if self.has_edge(id, to) {
+                        crate::println!("  -> {}", self.describe(to));
+                    }
+                }
             }
+            crate::println!(
+                "{} classes, {} edges, {} inversions",
+                self.classes.len(),
+                self.edges.len(),
+                LOCKDEP_INVERSIONS.value()
+            );
         }
-        println!("  }}");
     }
 
-    println!("\nConnected sets:");
-    // TODO: Implement connected set dumping
-}
-
-/// System callback: circular lock dependency detected
-fn system_circular_lock_dependency_detected(_root_id: LockClassId) {
-    println!("\nRUSTUX KERNEL OOPS");
-    println!("Circular lock dependency detected:");
+    /// `INSIDE` when no CPU is in the validator
+    const NO_CPU: u32 = u32::MAX;
 
-    let classes = LOCK_CLASSES.lock();
-    for (_id, class) in classes.iter() {
-        println!("  {}", class.name());
-    }
-    println!();
-}
+    static GRAPH: spin::Mutex<Graph> = spin::Mutex::new(Graph::new());
 
-/// System callback: lock validation error
-fn system_lock_validation_error(
-    _bad_entry: &AcquiredLockEntry,
-    _conflicting_entry: &AcquiredLockEntry,
-    _caller_address: usize,
-    _caller_frame: usize,
-    result: LockResult,
-) {
-    println!("\nRUSTUX KERNEL PANIC");
-    println!("Lock validation failed:");
-    println!("Reason: {}", result.to_string());
-    // TODO: Print more detailed information
-}
+    /// CPU in the validator, to ignore locks the validator takes itself
+    static INSIDE: AtomicU32 = AtomicU32::new(NO_CPU);
 
-/// System callback: fatal lock violation
-fn system_lock_validation_fatal(
-    _lock_entry: &AcquiredLockEntry,
-    _caller_address: usize,
-    _caller_frame: usize,
-    result: LockResult,
-) -> ! {
-    println!("\nRUSTUX KERNEL PANIC");
-    println!("Fatal lock violation detected! reason={}", result.to_string());
+    fn this_cpu() -> u32 {
+        if percpu::num_cpus() == 0 {
+            0
+        } else {
+            percpu::current_cpu_num()
+        }
+    }
 
-    // TODO: Print backtrace and halt
+    fn current_thread() -> ThreadId {
+        if percpu::num_cpus() == 0 {
+            return 0;
+        }
+        unsafe { percpu::get_percpu(this_cpu() as usize) }.current_thread()
+    }
 
-    loop {
-        core::hint::spin_loop();
+    /// Run `f` on the graph, or skip it if this CPU is already inside
+    pub fn with_graph<R>(f: impl FnOnce(&mut Graph) -> R) -> Option<R> {
+        let cpu = this_cpu();
+        if INSIDE.load(Ordering::Relaxed) == cpu {
+            return None;
+        }
+        let state = arch::arch_interrupt_save();
+        let result = {
+            let mut graph = GRAPH.lock();
+            INSIDE.store(cpu, Ordering::Relaxed);
+            let result = f(&mut graph);
+            INSIDE.store(NO_CPU, Ordering::Relaxed);
+            result
+        };
+        unsafe { arch::arch_interrupt_restore(state) };
+        Some(result)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub(super) fn acquire(class: &Class, lock: usize, kind: LockKind, try_lock: bool) {
+        with_graph(|graph| {
+            let id = match class.id.load(Ordering::Relaxed) {
+                0 => match graph.register(class.site, kind) {
+                    Some(id) => {
+                        class.id.store(id, Ordering::Relaxed);
+                        id
+                    }
+                    None => {
+                        LOCKDEP_CLASSES_FULL.add(1);
+                        return;
+                    }
+                },
+                id => id,
+            };
+            for inversion in graph.acquire(current_thread(), id, lock, try_lock, Backtrace::capture) {
+                graph.report(&inversion);
+            }
+        });
+    }
 
-    #[test]
-    fn test_lock_class_registration() {
-        let id1 = register_lock_class("test_lock1");
-        let id2 = register_lock_class("test_lock2");
+    pub(super) fn release(lock: usize) {
+        with_graph(|graph| graph.release(current_thread(), lock));
+    }
 
-        assert!(id1 < id2);
+    pub(super) fn inversions() -> i64 {
+        LOCKDEP_INVERSIONS.value()
     }
 
-    #[test]
-    fn test_acquired_lock_entry() {
-        let entry = AcquiredLockEntry::new(42);
-        assert_eq!(entry.id(), 42);
-        assert_eq!(entry.order(), 0); // First lock
+    fn cmd_lockdep(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+        with_graph(|graph| graph.dump());
+        0
     }
 
-    #[test]
-    fn test_thread_lock_state() {
-        let mut state = ThreadLockState::new();
+    crate::static_command!("lockdep", "lock classes and the order they are taken in", cmd_lockdep);
 
-        assert_eq!(state.lock_depth(), 0);
+    /// ========================================================================
+    /// Tests
+    /// ========================================================================
 
-        let entry = AcquiredLockEntry::new(1);
-        state.push_lock(entry);
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-        assert_eq!(state.lock_depth(), 1);
+        /// Three classes, created on different lines
+        fn classes(graph: &mut Graph) -> [LockClassId; 3] {
+            let sites = [
+                Location::caller(),
+                Location::caller(),
+                Location::caller(),
+            ];
+            sites.map(|site| graph.register(site, LockKind::Spin).unwrap())
+        }
 
-        let popped = state.pop_lock();
-        assert!(popped.is_some());
-        assert_eq!(popped.unwrap().id(), 1);
-        assert_eq!(state.lock_depth(), 0);
-    }
+        #[test]
+        fn test_inversion_reported_once() {
+            let mut graph = Graph::new();
+            let c = classes(&mut graph);
+            let (a, b) = (c[0], c[1]);
+
+            assert!(graph.acquire(1, a, 0x100, false, Backtrace::new).is_empty());
+            assert!(graph.acquire(1, b, 0x200, false, Backtrace::new).is_empty());
+            graph.release(1, 0x200);
+            graph.release(1, 0x100);
+
+            graph.acquire(2, b, 0x200, false, Backtrace::new);
+            let inversions = graph.acquire(2, a, 0x100, false, Backtrace::new);
+            assert_eq!(inversions.len(), 1);
+            assert_eq!((inversions[0].taking, inversions[0].held), (a, b));
+            assert_eq!(inversions[0].path, [a, b]);
+            graph.release(2, 0x100);
+            graph.release(2, 0x200);
+
+            graph.acquire(3, b, 0x200, false, Backtrace::new);
+            assert!(graph.acquire(3, a, 0x100, false, Backtrace::new).is_empty());
+        }
 
-    #[test]
-    fn test_lock_result_string() {
-        assert_eq!(LockResult::Ok.to_string(), "Ok");
-        assert_eq!(
-            LockResult::OrderViolation.to_string(),
-            "Order violation"
-        );
-        assert_eq!(
-            LockResult::CircularDependency.to_string(),
-            "Circular dependency"
-        );
-        assert_eq!(
-            LockResult::InvalidLockClass.to_string(),
-            "Invalid lock class"
-        );
-    }
+        #[test]
+        fn test_transitive_cycle() {
+            let mut graph = Graph::new();
+            let c = classes(&mut graph);
+
+            graph.acquire(1, c[0], 0x100, false, Backtrace::new);
+            graph.acquire(1, c[1], 0x200, false, Backtrace::new);
+            graph.release(1, 0x100);
+            graph.acquire(1, c[2], 0x300, false, Backtrace::new);
+            graph.release(1, 0x300);
+            graph.release(1, 0x200);
+
+            graph.acquire(1, c[2], 0x300, false, Backtrace::new);
+            let inversions = graph.acquire(1, c[0], 0x100, false, Backtrace::new);
+            assert_eq!(inversions.len(), 1);
+            assert_eq!(inversions[0].path, [c[0], c[1], c[2]]);
+        }
 
-    #[test]
-    fn test_constants() {
-        assert_eq!(MAX_LOCK_CLASSES, 256);
+        #[test]
+        fn test_try_lock_and_same_class() {
+            let mut graph = Graph::new();
+            let c = classes(&mut graph);
+            assert_eq!(graph.register(graph.class(c[0]).site, LockKind::Spin), Some(c[0]));
+
+            graph.acquire(1, c[0], 0x100, false, Backtrace::new);
+            graph.acquire(1, c[1], 0x200, true, Backtrace::new);
+            graph.acquire(1, c[0], 0x180, false, Backtrace::new);
+            assert!(!graph.has_edge(c[0], c[1]));
+            assert!(!graph.has_edge(c[0], c[0]));
+
+            // Released out of order, by lock rather than class
+            graph.release(1, 0x100);
+            assert_eq!(graph.held[&1].len(), 2);
+        }
     }
 }
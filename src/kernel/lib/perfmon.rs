@@ -0,0 +1,533 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Performance Monitoring
+//!
+//! Counts hardware events with the CPU's performance counters: Intel
+//! architectural performance monitoring on x86_64 and PMUv3 on arm64.
+//! riscv64 reports no counters. Configured with `rx_perfmon_control` and
+//! read with `rx_perfmon_read`; the `perf` tool drives both.
+//!
+//! # Modes
+//!
+//! - **CPU**: every CPU counts everything it runs, into its own totals
+//! - **Thread**: one thread is counted wherever it runs. Its counters are
+//!   loaded when it is switched in and added to its totals when it is
+//!   switched out, so it is counted from its next switch-in.
+//!
+//! # Overflow
+//!
+//! Every counter interrupts on overflow, and the handler adds the wrapped
+//! count to the totals. With a sample period, counter 0 starts `period`
+//! events short of overflowing and is reloaded the same way each time; its
+//! overflow also records a profiler sample of the interrupted code, so
+//! `prof dump` shows where the events happen.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use crate::kernel::arch::arch_traits::ArchPerfmon;
+use crate::kernel::lib::profiler;
+use crate::kernel::mp::{self, MpIpiTarget};
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::thread::{self, ThreadId, TID_INVALID};
+use crate::rustux::types::err::*;
+use crate::rustux::types::Status;
+
+use crate::log_info;
+
+#[cfg(target_arch = "x86_64")]
+type Arch = crate::kernel::arch::amd64::Amd64Arch;
+
+#[cfg(target_arch = "aarch64")]
+type Arch = crate::kernel::arch::arm64::Arm64Arch;
+
+#[cfg(target_arch = "riscv64")]
+type Arch = crate::kernel::arch::riscv64::Riscv64Arch;
+
+/// Most counters in a configuration
+pub const PERFMON_MAX_COUNTERS: usize = 4;
+
+/// Generic events
+pub const PERFMON_EVENT_CYCLES: u32 = 1;
+pub const PERFMON_EVENT_INSTRUCTIONS: u32 = 2;
+pub const PERFMON_EVENT_CACHE_MISSES: u32 = 3;
+pub const PERFMON_EVENT_BRANCH_MISSES: u32 = 4;
+
+/// Highest generic event
+const PERFMON_EVENT_LAST: u32 = PERFMON_EVENT_BRANCH_MISSES;
+
+/// A raw hardware event in the low 16 bits: `umask << 8 | event` on
+/// x86_64, the PMUv3 event number on arm64
+pub const PERFMON_EVENT_RAW: u32 = 1 << 31;
+
+/// Count every CPU
+pub const PERFMON_MODE_CPU: u32 = 0;
+
+/// Count one thread
+pub const PERFMON_MODE_THREAD: u32 = 1;
+
+/// Count user code
+pub const PERFMON_FLAG_USER: u32 = 1 << 0;
+
+/// Count kernel code
+pub const PERFMON_FLAG_KERNEL: u32 = 1 << 1;
+
+/// Interrupt on overflow; set by the kernel, not accepted from userspace
+pub const PERFMON_FLAG_INTERRUPT: u32 = 1 << 2;
+
+/// `cpu` of the totals read back in thread mode
+pub const PERFMON_CPU_THREAD: u32 = u32::MAX;
+
+/// Shortest sample period, in events
+pub const PERFMON_MIN_SAMPLE_PERIOD: u64 = 1000;
+
+/// What the PMU can do, as read by `GET_PROPERTIES`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfmonProperties {
+    /// Counters usable at once
+    pub counters: u32,
+
+    /// Width of the counters in bits
+    pub counter_bits: u32,
+
+    /// Bit `n` is set if generic event `n` can be counted
+    pub events: u32,
+
+    /// Reserved, 0
+    pub reserved: u32,
+}
+
+/// A counting session, as passed to `START`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfmonConfig {
+    /// Event of each counter, `PERFMON_EVENT_*`
+    pub events: [u32; PERFMON_MAX_COUNTERS],
+
+    /// Counters in use
+    pub count: u32,
+
+    /// `PERFMON_MODE_*`
+    pub mode: u32,
+
+    /// `PERFMON_FLAG_USER` and `PERFMON_FLAG_KERNEL`, 0 for both
+    pub flags: u32,
+
+    /// Reserved, 0
+    pub reserved: u32,
+
+    /// Thread handle to count in thread mode
+    pub thread: u64,
+
+    /// Events of counter 0 between profiler samples, 0 for none
+    pub sample_period: u64,
+}
+
+/// Totals of one CPU or of the counted thread, as read by `rx_perfmon_read`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfmonCounts {
+    /// CPU, or `PERFMON_CPU_THREAD`
+    pub cpu: u32,
+
+    /// Entries of `values` in use
+    pub count: u32,
+
+    /// Events counted by each counter
+    pub values: [u64; PERFMON_MAX_COUNTERS],
+}
+
+/// Whether a session is counting
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Configuration of the running or last session; changed only while
+/// stopped
+static CONFIG: SpinMutex<PerfmonConfig> = SpinMutex::new(PerfmonConfig {
+    events: [0; PERFMON_MAX_COUNTERS],
+    count: 0,
+    mode: PERFMON_MODE_CPU,
+    flags: 0,
+    reserved: 0,
+    thread: 0,
+    sample_period: 0,
+});
+
+/// Mode and thread of the session, readable without the lock from the
+/// context switch and overflow paths
+static MODE: AtomicU32 = AtomicU32::new(PERFMON_MODE_CPU);
+static TARGET: AtomicU64 = AtomicU64::new(TID_INVALID);
+
+/// Hardware event and flags of each counter
+static EVENTS: [AtomicU64; PERFMON_MAX_COUNTERS] = [const { AtomicU64::new(0) }; PERFMON_MAX_COUNTERS];
+static FLAGS: AtomicU32 = AtomicU32::new(0);
+static COUNT: AtomicU32 = AtomicU32::new(0);
+static PERIOD: AtomicU64 = AtomicU64::new(0);
+
+/// One CPU's counters, on its own cache line
+#[repr(align(64))]
+struct CpuCounters {
+    /// Events counted, in CPU mode
+    totals: [AtomicU64; PERFMON_MAX_COUNTERS],
+
+    /// Value each counter was loaded with
+    base: [AtomicU64; PERFMON_MAX_COUNTERS],
+
+    /// Whether the counters are loaded
+    loaded: AtomicBool,
+}
+
+impl CpuCounters {
+    const fn new() -> Self {
+        Self {
+            totals: [const { AtomicU64::new(0) }; PERFMON_MAX_COUNTERS],
+            base: [const { AtomicU64::new(0) }; PERFMON_MAX_COUNTERS],
+            loaded: AtomicBool::new(false),
+        }
+    }
+}
+
+static CPUS: [CpuCounters; SMP_MAX_CPUS] = [const { CpuCounters::new() }; SMP_MAX_CPUS];
+
+/// Events counted for the thread, in thread mode
+static THREAD_TOTALS: [AtomicU64; PERFMON_MAX_COUNTERS] = [const { AtomicU64::new(0) }; PERFMON_MAX_COUNTERS];
+
+/// Log what the PMU offers
+pub fn init() {
+    let props = properties();
+    if props.counters == 0 {
+        log_info!("perfmon: no performance counters");
+        return;
+    }
+    log_info!(
+        "perfmon: {} counters of {} bits, events {:#x}",
+        props.counters,
+        props.counter_bits,
+        props.events
+    );
+}
+
+/// What the PMU can do
+pub fn properties() -> PerfmonProperties {
+    let counters = Arch::perfmon_counter_count().min(PERFMON_MAX_COUNTERS as u32);
+    if counters == 0 {
+        return PerfmonProperties::default();
+    }
+    let events = (1..=PERFMON_EVENT_LAST)
+        .filter(|&event| Arch::perfmon_event(event).is_some())
+        .fold(0, |mask, event| mask | 1 << event);
+    PerfmonProperties {
+        counters,
+        counter_bits: Arch::perfmon_counter_bits(),
+        events,
+        reserved: 0,
+    }
+}
+
+/// Hardware event for a `PERFMON_EVENT_*` event
+fn hw_event(event: u32) -> Option<u64> {
+    if event & PERFMON_EVENT_RAW != 0 {
+        return (event & !PERFMON_EVENT_RAW <= 0xffff).then_some((event & 0xffff) as u64);
+    }
+    Arch::perfmon_event(event)
+}
+
+/// Check a configuration against the PMU
+fn validate(config: &PerfmonConfig, props: &PerfmonProperties) -> Result<(), Status> {
+    if props.counters == 0 {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    if config.count == 0
+        || config.count > props.counters
+        || config.flags & !(PERFMON_FLAG_USER | PERFMON_FLAG_KERNEL) != 0
+        || config.reserved != 0
+        || (config.sample_period != 0 && config.sample_period < PERFMON_MIN_SAMPLE_PERIOD)
+        || config.sample_period >= 1u64 << (props.counter_bits.min(32) - 1)
+    {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    match config.mode {
+        PERFMON_MODE_CPU => Ok(()),
+        PERFMON_MODE_THREAD if config.thread != TID_INVALID => Ok(()),
+        _ => Err(RX_ERR_INVALID_ARGS),
+    }
+}
+
+/// Start counting
+///
+/// Fails with `BAD_STATE` if a session is already running and
+/// `NOT_SUPPORTED` if the PMU can't count one of the events.
+pub fn start(config: &PerfmonConfig) -> Result<(), Status> {
+    let props = properties();
+    validate(config, &props)?;
+
+    let mut events = [0u64; PERFMON_MAX_COUNTERS];
+    for (hw, &event) in events.iter_mut().zip(&config.events[..config.count as usize]) {
+        *hw = hw_event(event).ok_or(RX_ERR_NOT_SUPPORTED)?;
+    }
+    if config.mode == PERFMON_MODE_THREAD && thread::get_thread_by_id(config.thread).is_none() {
+        return Err(RX_ERR_BAD_HANDLE);
+    }
+
+    let mut current = CONFIG.lock();
+    if RUNNING.load(Ordering::Acquire) {
+        return Err(RX_ERR_BAD_STATE);
+    }
+    *current = *config;
+
+    for (slot, &event) in EVENTS.iter().zip(&events) {
+        slot.store(event, Ordering::Relaxed);
+    }
+    let flags = match config.flags {
+        0 => PERFMON_FLAG_USER | PERFMON_FLAG_KERNEL,
+        flags => flags,
+    };
+    FLAGS.store(flags | PERFMON_FLAG_INTERRUPT, Ordering::Relaxed);
+    COUNT.store(config.count, Ordering::Relaxed);
+    PERIOD.store(config.sample_period, Ordering::Relaxed);
+    MODE.store(config.mode, Ordering::Relaxed);
+    TARGET.store(if config.mode == PERFMON_MODE_THREAD { config.thread } else { TID_INVALID }, Ordering::Relaxed);
+
+    for cpu in CPUS.iter() {
+        cpu.totals.iter().for_each(|total| total.store(0, Ordering::Relaxed));
+    }
+    THREAD_TOTALS.iter().for_each(|total| total.store(0, Ordering::Relaxed));
+
+    RUNNING.store(true, Ordering::Release);
+    drop(current);
+
+    if config.mode == PERFMON_MODE_CPU {
+        mp::mp_sync_exec(MpIpiTarget::All, 0, load_task, 0);
+    }
+    log_info!("perfmon: started, {} counters, mode {}", config.count, config.mode);
+    Ok(())
+}
+
+/// Stop counting and collect every CPU's counts
+pub fn stop() {
+    let _config = CONFIG.lock();
+    if !RUNNING.load(Ordering::Acquire) {
+        return;
+    }
+    mp::mp_sync_exec(MpIpiTarget::All, 0, unload_task, 0);
+    RUNNING.store(false, Ordering::Release);
+    log_info!("perfmon: stopped");
+}
+
+unsafe fn load_task(_context: u64) {
+    load();
+}
+
+unsafe fn unload_task(_context: u64) {
+    unload();
+}
+
+/// Load the session's counters on this CPU and start them
+fn load() {
+    let Some(cpu) = CPUS.get(percpu::current_cpu_num() as usize) else {
+        return;
+    };
+    let count = COUNT.load(Ordering::Relaxed);
+    let flags = FLAGS.load(Ordering::Relaxed);
+
+    unsafe {
+        Arch::perfmon_init();
+        for i in 0..count {
+            let base = start_value(i);
+            cpu.base[i as usize].store(base, Ordering::Relaxed);
+            Arch::perfmon_program(i, EVENTS[i as usize].load(Ordering::Relaxed), base, flags);
+        }
+        cpu.loaded.store(true, Ordering::Relaxed);
+        Arch::perfmon_enable((1 << count) - 1);
+    }
+}
+
+/// Stop this CPU's counters and add what they counted to the totals
+fn unload() {
+    let cpu_num = percpu::current_cpu_num() as usize;
+    let Some(cpu) = CPUS.get(cpu_num) else {
+        return;
+    };
+    if !cpu.loaded.swap(false, Ordering::Relaxed) {
+        return;
+    }
+
+    unsafe { Arch::perfmon_enable(0) };
+    let mask = counter_mask();
+    for i in 0..COUNT.load(Ordering::Relaxed) as usize {
+        let counted = Arch::perfmon_read(i as u32).wrapping_sub(cpu.base[i].load(Ordering::Relaxed)) & mask;
+        add(cpu_num, i, counted);
+    }
+}
+
+/// Value counter `index` starts from
+fn start_value(index: u32) -> u64 {
+    match PERIOD.load(Ordering::Relaxed) {
+        period if index == 0 && period != 0 => counter_mask().wrapping_sub(period - 1) & counter_mask(),
+        _ => 0,
+    }
+}
+
+/// Largest value a counter holds
+fn counter_mask() -> u64 {
+    match Arch::perfmon_counter_bits() {
+        0 => 0,
+        bits if bits >= 64 => u64::MAX,
+        bits => (1 << bits) - 1,
+    }
+}
+
+/// Add `counted` events of counter `index` to the session's totals
+fn add(cpu: usize, index: usize, counted: u64) {
+    if MODE.load(Ordering::Relaxed) == PERFMON_MODE_THREAD {
+        THREAD_TOTALS[index].fetch_add(counted, Ordering::Relaxed);
+    } else {
+        CPUS[cpu].totals[index].fetch_add(counted, Ordering::Relaxed);
+    }
+}
+
+/// Move the counted thread's counters on and off this CPU
+///
+/// Called by the scheduler when it switches from `prev` to `next`.
+#[inline]
+pub fn context_switch(prev: Option<ThreadId>, next: ThreadId) {
+    if !RUNNING.load(Ordering::Relaxed) || MODE.load(Ordering::Relaxed) != PERFMON_MODE_THREAD {
+        return;
+    }
+    let target = TARGET.load(Ordering::Relaxed);
+    if prev == Some(target) {
+        unload();
+    }
+    if next == target {
+        load();
+    }
+}
+
+/// Handle a counter overflow interrupt
+///
+/// Adds the wrapped counts to the totals and reloads the sampling
+/// counter, recording a profiler sample of the interrupted context.
+/// Returns whether any counter had overflowed.
+pub fn overflow(pc: u64, fp: u64, user: bool) -> bool {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return false;
+    }
+    let overflowed = unsafe { Arch::perfmon_ack_overflow() };
+    if overflowed == 0 {
+        return false;
+    }
+
+    let cpu_num = percpu::current_cpu_num() as usize;
+    let Some(cpu) = CPUS.get(cpu_num) else {
+        return true;
+    };
+    let mask = counter_mask();
+    let count = COUNT.load(Ordering::Relaxed) as usize;
+
+    for i in (0..count).filter(|i| overflowed & (1 << i) != 0) {
+        let base = cpu.base[i].load(Ordering::Relaxed);
+        add(cpu_num, i, (mask - base).wrapping_add(1));
+
+        let reload = start_value(i as u32);
+        cpu.base[i].store(reload, Ordering::Relaxed);
+        if reload != 0 {
+            unsafe {
+                Arch::perfmon_enable(0);
+                Arch::perfmon_program(
+                    i as u32,
+                    EVENTS[i].load(Ordering::Relaxed),
+                    reload,
+                    FLAGS.load(Ordering::Relaxed),
+                );
+                Arch::perfmon_enable((1 << count) - 1);
+            }
+            profiler::record(pc, fp, user);
+        }
+    }
+    true
+}
+
+/// Copy the totals of the running or last session, from entry `first`,
+/// into `out`
+///
+/// In CPU mode there is one entry per CPU, in thread mode one for the
+/// thread. Totals only include what was collected when counters were
+/// switched out or overflowed, so read after `stop` for exact counts.
+/// Returns the number of entries written, 0 at the end.
+pub fn read(first: usize, out: &mut [PerfmonCounts]) -> usize {
+    let config = *CONFIG.lock();
+    let values = |totals: &[AtomicU64; PERFMON_MAX_COUNTERS]| {
+        let mut values = [0u64; PERFMON_MAX_COUNTERS];
+        for (value, total) in values.iter_mut().zip(totals) {
+            *value = total.load(Ordering::Relaxed);
+        }
+        values
+    };
+
+    if config.mode == PERFMON_MODE_THREAD {
+        let Some(slot) = out.first_mut().filter(|_| first == 0) else {
+            return 0;
+        };
+        *slot = PerfmonCounts {
+            cpu: PERFMON_CPU_THREAD,
+            count: config.count,
+            values: values(&THREAD_TOTALS),
+        };
+        return 1;
+    }
+
+    let cpus = (percpu::num_cpus() as usize).clamp(1, SMP_MAX_CPUS);
+    let mut written = 0;
+    for (cpu, slot) in (first..cpus).zip(out.iter_mut()) {
+        *slot = PerfmonCounts {
+            cpu: cpu as u32,
+            count: config.count,
+            values: values(&CPUS[cpu].totals),
+        };
+        written += 1;
+    }
+    written
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(core::mem::size_of::<PerfmonProperties>(), 16);
+        assert_eq!(core::mem::size_of::<PerfmonConfig>(), 48);
+        assert_eq!(core::mem::size_of::<PerfmonCounts>(), 40);
+    }
+
+    #[test]
+    fn test_validate() {
+        let props = PerfmonProperties { counters: 4, counter_bits: 48, events: 0x1e, reserved: 0 };
+        let config = PerfmonConfig { count: 2, ..Default::default() };
+        assert_eq!(validate(&config, &props), Ok(()));
+
+        assert_eq!(validate(&config, &PerfmonProperties::default()), Err(RX_ERR_NOT_SUPPORTED));
+        assert_eq!(validate(&PerfmonConfig { count: 5, ..config }, &props), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&PerfmonConfig { flags: PERFMON_FLAG_INTERRUPT, ..config }, &props), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&PerfmonConfig { sample_period: 10, ..config }, &props), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(validate(&PerfmonConfig { mode: PERFMON_MODE_THREAD, ..config }, &props), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(
+            validate(&PerfmonConfig { mode: PERFMON_MODE_THREAD, thread: 5, ..config }, &props),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_raw_events() {
+        assert_eq!(hw_event(PERFMON_EVENT_RAW | 0x412e), Some(0x412e));
+        assert_eq!(hw_event(PERFMON_EVENT_RAW | 0x1_0000), None);
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Sampling Profiler
+//!
+//! Periodically records where each CPU is executing: the interrupted pc,
+//! the current thread and process, and for kernel code a short frame
+//! pointer backtrace. Read out with `rx_profiler_read` and folded into
+//! flamegraph stacks by the `prof` tool.
+//!
+//! # Design
+//!
+//! Samples are taken from the timer interrupt (any interrupt on arm64,
+//! where the timer is not told apart from other IRQs). Each CPU keeps its
+//! own deadline and skips interrupts that arrive before it, so the rate
+//! is at most one sample per period and at most the tick rate.
+//!
+//! Every CPU owns a ring of fixed-size [`ProfilerSample`]s, written the
+//! same way as the ktrace rings: one relaxed `fetch_add` claims a slot and
+//! the oldest samples are overwritten when the ring is full.
+//!
+//! User samples carry only the pc. Walking a user stack from an interrupt
+//! could fault, and a fault can't be taken there.
+//!
+//! The performance counters also record samples, through [`record`], when
+//! a counter with a sample period overflows. Those go into the same rings
+//! whether or not timer sampling is running.
+//!
+//! # Command Line
+//!
+//! - `profiler.bufsize=<KiB>` - Ring size per CPU (default 256, 0 disables)
+//! - `profiler.period=<us>` - Default sampling period (default 1000)
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+use crate::kernel::cmdline;
+use crate::kernel::lib::backtrace::Backtrace;
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::thread;
+use crate::kernel::timer;
+
+use crate::log_info;
+
+/// Default ring size per CPU, in KiB
+const DEFAULT_BUFSIZE_KB: u32 = 256;
+
+/// Default sampling period, in microseconds
+const DEFAULT_PERIOD_US: u32 = 1000;
+
+/// Shortest sampling period, in microseconds
+pub const PROFILER_MIN_PERIOD_US: u32 = 10;
+
+/// Frames kept per sample, the sampled pc included
+pub const PROFILER_MAX_FRAMES: usize = 8;
+
+/// The sample interrupted user code
+pub const SAMPLE_FLAG_USER: u16 = 1 << 0;
+
+/// One sample, as read out by `rx_profiler_read`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfilerSample {
+    /// Monotonic time in nanoseconds
+    pub ts: u64,
+
+    /// Process of the interrupted thread, 0 for kernel threads or if it
+    /// couldn't be looked up
+    pub pid: u64,
+
+    /// Interrupted thread
+    pub tid: u64,
+
+    /// CPU that took the sample
+    pub cpu: u16,
+
+    /// `SAMPLE_FLAG_*` bits
+    pub flags: u16,
+
+    /// Entries of `pcs` in use
+    pub depth: u16,
+
+    /// Reserved, 0
+    pub reserved: u16,
+
+    /// Sampled pc, then return addresses, innermost first
+    pub pcs: [u64; PROFILER_MAX_FRAMES],
+}
+
+impl ProfilerSample {
+    /// Size of a sample in bytes
+    pub const SIZE: usize = core::mem::size_of::<Self>();
+
+    const EMPTY: Self = Self {
+        ts: 0,
+        pid: 0,
+        tid: 0,
+        cpu: 0,
+        flags: 0,
+        depth: 0,
+        reserved: 0,
+        pcs: [0; PROFILER_MAX_FRAMES],
+    };
+}
+
+/// One CPU's ring, on its own cache line
+#[repr(align(64))]
+struct Ring {
+    /// Samples, `CAPACITY` of them, or null before `init`
+    samples: AtomicPtr<ProfilerSample>,
+
+    /// Samples ever written; the next slot is `head % CAPACITY`
+    head: AtomicU64,
+
+    /// Time of this CPU's next sample
+    deadline: AtomicU64,
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Self {
+            samples: AtomicPtr::new(core::ptr::null_mut()),
+            head: AtomicU64::new(0),
+            deadline: AtomicU64::new(0),
+        }
+    }
+}
+
+static RINGS: [Ring; SMP_MAX_CPUS] = [const { Ring::new() }; SMP_MAX_CPUS];
+
+/// Samples per ring
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether samples are being taken
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Sampling period in nanoseconds
+static PERIOD: AtomicU64 = AtomicU64::new(DEFAULT_PERIOD_US as u64 * 1000);
+
+/// Allocate the per-CPU rings
+pub fn init() {
+    let kb = cmdline::cmdline_get_uint32("profiler.bufsize", DEFAULT_BUFSIZE_KB) as usize;
+    let capacity = kb * 1024 / ProfilerSample::SIZE;
+    if capacity == 0 {
+        log_info!("profiler: disabled");
+        return;
+    }
+
+    let cpus = (percpu::num_cpus() as usize).clamp(1, SMP_MAX_CPUS);
+    for ring in &RINGS[..cpus] {
+        let mut samples = Vec::with_capacity(capacity);
+        samples.resize(capacity, ProfilerSample::EMPTY);
+        ring.samples.store(samples.leak().as_mut_ptr(), Ordering::Release);
+    }
+    CAPACITY.store(capacity, Ordering::Release);
+
+    let period = cmdline::cmdline_get_uint32("profiler.period", DEFAULT_PERIOD_US);
+    PERIOD.store(period_ns(period), Ordering::Relaxed);
+
+    log_info!("profiler: {} samples per CPU on {} CPUs", capacity, cpus);
+}
+
+/// Whether the rings were allocated
+pub fn available() -> bool {
+    CAPACITY.load(Ordering::Acquire) != 0
+}
+
+/// Period in nanoseconds for `us` microseconds, 0 meaning the default
+fn period_ns(us: u32) -> u64 {
+    let us = if us == 0 { DEFAULT_PERIOD_US } else { us.max(PROFILER_MIN_PERIOD_US) };
+    us as u64 * 1000
+}
+
+/// Start sampling every `period_us` microseconds (0 for the default)
+pub fn start(period_us: u32) {
+    PERIOD.store(period_ns(period_us), Ordering::Relaxed);
+    for ring in RINGS.iter() {
+        ring.deadline.store(0, Ordering::Relaxed);
+    }
+    RUNNING.store(true, Ordering::Release);
+    log_info!("profiler: started, period {}us", PERIOD.load(Ordering::Relaxed) / 1000);
+}
+
+/// Stop sampling
+pub fn stop() {
+    RUNNING.store(false, Ordering::Release);
+    log_info!("profiler: stopped");
+}
+
+/// Discard every sample
+///
+/// Samples taken concurrently may survive; rewind while stopped.
+pub fn rewind() {
+    for ring in RINGS.iter() {
+        ring.head.store(0, Ordering::Release);
+    }
+    log_info!("profiler: rewound");
+}
+
+/// Take a sample of the interrupted context, if this CPU is due one
+///
+/// Called with interrupts disabled. `fp` is the interrupted frame
+/// pointer, or 0 if the architecture's interrupt frame doesn't save it.
+#[inline]
+pub fn sample(pc: u64, fp: u64, user: bool) {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let cpu = percpu::current_cpu_num() as usize;
+    if cpu >= SMP_MAX_CPUS {
+        return;
+    }
+
+    let ring = &RINGS[cpu];
+    let now = timer::current_time();
+    if now < ring.deadline.load(Ordering::Relaxed) {
+        return;
+    }
+    ring.deadline.store(now + PERIOD.load(Ordering::Relaxed), Ordering::Relaxed);
+
+    write(cpu, now, pc, fp, user);
+}
+
+/// Record a sample of the interrupted context now
+///
+/// Like [`sample`], but whether or not sampling is running and regardless
+/// of the period.
+pub fn record(pc: u64, fp: u64, user: bool) {
+    let cpu = percpu::current_cpu_num() as usize;
+    if cpu < SMP_MAX_CPUS {
+        write(cpu, timer::current_time(), pc, fp, user);
+    }
+}
+
+/// Write a sample to `cpu`'s ring
+fn write(cpu: usize, now: u64, pc: u64, fp: u64, user: bool) {
+    let ring = &RINGS[cpu];
+    let samples = ring.samples.load(Ordering::Acquire);
+    if samples.is_null() {
+        return;
+    }
+
+    let mut sample = ProfilerSample {
+        ts: now,
+        pid: thread::try_current_pid().unwrap_or(0),
+        tid: thread::current_thread_id(),
+        cpu: cpu as u16,
+        flags: if user { SAMPLE_FLAG_USER } else { 0 },
+        ..ProfilerSample::EMPTY
+    };
+
+    if user {
+        sample.pcs[0] = pc;
+        sample.depth = 1;
+    } else {
+        let bt = unsafe { Backtrace::from_context(pc as usize, fp as usize) };
+        for (slot, &frame) in sample.pcs.iter_mut().zip(bt.frames()) {
+            *slot = frame as u64;
+            sample.depth += 1;
+        }
+    }
+
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    let seq = ring.head.fetch_add(1, Ordering::Relaxed);
+    unsafe { core::ptr::write_volatile(samples.add((seq % capacity as u64) as usize), sample) };
+}
+
+/// Oldest retained sample and number of retained samples of a ring that
+/// has had `head` samples written to `capacity` slots
+fn window(head: u64, capacity: usize) -> (u64, usize) {
+    let len = head.min(capacity as u64);
+    (head - len, len as usize)
+}
+
+/// Bytes of samples available to `read`
+pub fn size() -> usize {
+    let capacity = CAPACITY.load(Ordering::Acquire);
+    RINGS
+        .iter()
+        .map(|ring| window(ring.head.load(Ordering::Acquire), capacity).1)
+        .sum::<usize>()
+        * ProfilerSample::SIZE
+}
+
+/// Copy samples starting at byte `offset` into `buf`
+///
+/// The samples are each CPU's ring in turn, oldest first. `offset` and
+/// the length of `buf` are rounded down to whole samples. Returns the
+/// number of bytes copied, 0 at the end.
+pub fn read(offset: usize, buf: &mut [u8]) -> usize {
+    let capacity = CAPACITY.load(Ordering::Acquire);
+    let mut skip = offset / ProfilerSample::SIZE;
+    let mut copied = 0;
+
+    for ring in RINGS.iter() {
+        let samples = ring.samples.load(Ordering::Acquire);
+        if samples.is_null() {
+            continue;
+        }
+
+        let (start, len) = window(ring.head.load(Ordering::Acquire), capacity);
+        if skip >= len {
+            skip -= len;
+            continue;
+        }
+
+        for seq in start + skip as u64..start + len as u64 {
+            let out = match buf.get_mut(copied..copied + ProfilerSample::SIZE) {
+                Some(out) => out,
+                None => return copied,
+            };
+            let sample = unsafe {
+                core::ptr::read_volatile(samples.add((seq % capacity as u64) as usize))
+            };
+            out.copy_from_slice(unsafe {
+                core::slice::from_raw_parts(&sample as *const ProfilerSample as *const u8, ProfilerSample::SIZE)
+            });
+            copied += ProfilerSample::SIZE;
+        }
+        skip = 0;
+    }
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_layout() {
+        assert_eq!(ProfilerSample::SIZE, 96);
+        assert_eq!(core::mem::offset_of!(ProfilerSample, pcs), 32);
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(period_ns(0), DEFAULT_PERIOD_US as u64 * 1000);
+        assert_eq!(period_ns(1), PROFILER_MIN_PERIOD_US as u64 * 1000);
+        assert_eq!(period_ns(250), 250_000);
+    }
+
+    #[test]
+    fn test_window() {
+        assert_eq!(window(0, 8), (0, 0));
+        assert_eq!(window(8, 8), (0, 8));
+        assert_eq!(window(13, 8), (5, 8));
+    }
+}
@@ -0,0 +1,745 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Object Caches
+//!
+//! A slab allocator for fixed-size kernel objects. Each [`ObjectCache`]
+//! hands out objects of one size, carved from slabs of physically
+//! contiguous pages, with a per-CPU magazine of free objects in front so
+//! that most allocations and frees touch no shared lock.
+//!
+//! # Layout
+//!
+//! A slab is a power-of-two run of pages, aligned to its own size, from
+//! the PMM through the physmap. Its header sits at the start: the owning
+//! cache, a stack of free object indices, and the objects after that. An
+//! object's slab is found by masking its address, so freeing needs no
+//! lookup.
+//!
+//! # Magazines
+//!
+//! Each CPU keeps up to [`MAGAZINE_ROUNDS`] free objects. Allocation pops
+//! from the local magazine and refills half of it from the slabs when it
+//! runs dry; freeing pushes to it and returns half to the slabs when it
+//! is full.
+//!
+//! # Constructors and Destructors
+//!
+//! A cache may have a constructor, run on every object when its slab is
+//! created, and a destructor, run when the slab is given back to the PMM.
+//! Objects must be freed in their constructed state, so allocation skips
+//! the setup that every use of the object would repeat.
+//!
+//! # Typed Use
+//!
+//! `&'static ObjectCache` implements [`Allocator`], so a cache backs
+//! `Arc::new_in`, `Box::new_in` and `Vec::with_capacity_in`. Layouts that
+//! don't fit the cache's objects fall through to the heap.
+//!
+//! ```rust
+//! static HANDLE_CACHE: ObjectCache = ObjectCache::for_type::<HandleEntry>("handle");
+//!
+//! let entry = Box::try_new_in(HandleEntry { .. }, &HANDLE_CACHE)?;
+//! ```
+//!
+//! # Statistics
+//!
+//! The `slab` console command lists every cache that has allocated: slabs,
+//! objects in use out of objects carved, occupancy, and objects held in
+//! magazines. Low occupancy with many slabs means fragmentation;
+//! [`ObjectCache::reap`] gives empty slabs back.
+
+use alloc::alloc::{AllocError, Allocator, Global, Layout};
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::pmm::{self, PAGE_SIZE};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::sync::Mutex;
+
+/// Free objects each CPU's magazine holds
+pub const MAGAZINE_ROUNDS: usize = 8;
+
+/// Fewest objects a slab is sized for
+const MIN_OBJECTS_PER_SLAB: usize = 8;
+
+/// Round `value` up to a multiple of `align`, a power of two
+const fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+/// ============================================================================
+/// Slab Geometry
+/// ============================================================================
+
+/// How a cache's slabs are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Geometry {
+    /// Bytes per slab, a power of two and a multiple of the page size
+    slab_size: usize,
+
+    /// Distance between objects
+    stride: usize,
+
+    /// Objects per slab
+    capacity: usize,
+
+    /// Offset of the first object from the slab start
+    first: usize,
+}
+
+impl Geometry {
+    /// Lay out slabs for objects of `size` bytes aligned to `align`
+    const fn new(size: usize, align: usize) -> Self {
+        let stride = align_up(if size == 0 { 1 } else { size }, align);
+        let mut slab_size = PAGE_SIZE;
+        while Self::capacity(slab_size, stride, align) < MIN_OBJECTS_PER_SLAB {
+            slab_size *= 2;
+        }
+
+        let capacity = Self::capacity(slab_size, stride, align);
+        Self {
+            slab_size,
+            stride,
+            capacity,
+            first: Self::first(capacity, align),
+        }
+    }
+
+    /// Objects that fit a slab of `slab_size` bytes
+    const fn capacity(slab_size: usize, stride: usize, align: usize) -> usize {
+        // Header, then a u16 free index per object, then the objects
+        let header = core::mem::size_of::<SlabHeader>();
+        let mut capacity = (slab_size - header) / (stride + 2);
+        while capacity > 0 && Self::first(capacity, align) + capacity * stride > slab_size {
+            capacity -= 1;
+        }
+        if capacity > u16::MAX as usize {
+            u16::MAX as usize
+        } else {
+            capacity
+        }
+    }
+
+    /// Offset of the first object in a slab of `capacity` objects
+    const fn first(capacity: usize, align: usize) -> usize {
+        align_up(core::mem::size_of::<SlabHeader>() + capacity * 2, align)
+    }
+
+    /// Pages per slab
+    const fn pages(&self) -> usize {
+        self.slab_size / PAGE_SIZE
+    }
+}
+
+/// ============================================================================
+/// Slabs
+/// ============================================================================
+
+/// Header at the start of every slab
+#[repr(C)]
+struct SlabHeader {
+    /// Cache the slab belongs to
+    cache: *const ObjectCache,
+
+    /// Physical address of the slab, for freeing it
+    paddr: u64,
+
+    /// Entries on the free index stack
+    free: usize,
+}
+
+/// A slab, by its header
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Slab(NonNull<SlabHeader>);
+
+unsafe impl Send for Slab {}
+
+impl Slab {
+    /// Format `mem`, `geometry.slab_size` bytes aligned to that size, as
+    /// an empty slab of `cache`
+    ///
+    /// # Safety
+    ///
+    /// `mem` must be valid for writes of a whole slab and not in use.
+    unsafe fn init(mem: NonNull<u8>, paddr: u64, cache: *const ObjectCache, geometry: &Geometry) -> Self {
+        let header = mem.as_ptr() as *mut SlabHeader;
+        header.write(SlabHeader { cache, paddr, free: geometry.capacity });
+
+        let slab = Self(NonNull::new_unchecked(header));
+        for index in 0..geometry.capacity {
+            // Pop order is lowest address first
+            *slab.free_stack().add(index) = (geometry.capacity - 1 - index) as u16;
+        }
+        slab
+    }
+
+    /// Slab holding `obj`
+    fn of(obj: NonNull<u8>, geometry: &Geometry) -> Self {
+        let base = obj.as_ptr() as usize & !(geometry.slab_size - 1);
+        Self(unsafe { NonNull::new_unchecked(base as *mut SlabHeader) })
+    }
+
+    /// Free index stack, just past the header
+    fn free_stack(&self) -> *mut u16 {
+        unsafe { (self.0.as_ptr() as *mut u8).add(core::mem::size_of::<SlabHeader>()) as *mut u16 }
+    }
+
+    /// Object at `index`
+    fn object(&self, index: usize, geometry: &Geometry) -> NonNull<u8> {
+        let addr = self.0.as_ptr() as usize + geometry.first + index * geometry.stride;
+        unsafe { NonNull::new_unchecked(addr as *mut u8) }
+    }
+
+    /// Index of `obj`, which must lie in this slab
+    fn index_of(&self, obj: NonNull<u8>, geometry: &Geometry) -> usize {
+        (obj.as_ptr() as usize - self.0.as_ptr() as usize - geometry.first) / geometry.stride
+    }
+
+    /// The header; callers hold the cache's depot lock
+    #[allow(clippy::mut_from_ref)]
+    fn header(&self) -> &mut SlabHeader {
+        unsafe { &mut *self.0.as_ptr() }
+    }
+
+    /// Number of free objects
+    fn free_count(&self) -> usize {
+        self.header().free
+    }
+
+    /// Take a free object
+    fn pop(&self, geometry: &Geometry) -> Option<NonNull<u8>> {
+        let header = self.header();
+        if header.free == 0 {
+            return None;
+        }
+        header.free -= 1;
+        let index = unsafe { *self.free_stack().add(header.free) } as usize;
+        Some(self.object(index, geometry))
+    }
+
+    /// Give back `obj`
+    fn push(&self, obj: NonNull<u8>, geometry: &Geometry) {
+        let index = self.index_of(obj, geometry);
+        let header = self.header();
+        debug_assert!(index < geometry.capacity && header.free < geometry.capacity);
+        unsafe { *self.free_stack().add(header.free) = index as u16 };
+        header.free += 1;
+    }
+}
+
+/// Slabs of a cache, guarded by the cache's depot lock
+struct Depot {
+    /// Slabs with at least one free object, most recently used last
+    partial: Vec<Slab>,
+
+    /// All slabs the cache owns
+    slabs: usize,
+}
+
+/// ============================================================================
+/// Magazines
+/// ============================================================================
+
+/// A CPU's stack of free, constructed objects
+struct Magazine {
+    rounds: [Option<NonNull<u8>>; MAGAZINE_ROUNDS],
+    count: usize,
+}
+
+unsafe impl Send for Magazine {}
+
+impl Magazine {
+    const fn new() -> Self {
+        Self { rounds: [None; MAGAZINE_ROUNDS], count: 0 }
+    }
+
+    fn pop(&mut self) -> Option<NonNull<u8>> {
+        if self.count == 0 {
+            return None;
+        }
+        self.count -= 1;
+        self.rounds[self.count].take()
+    }
+
+    /// Push `obj`, handing it back if the magazine is full
+    fn push(&mut self, obj: NonNull<u8>) -> Result<(), NonNull<u8>> {
+        if self.count == MAGAZINE_ROUNDS {
+            return Err(obj);
+        }
+        self.rounds[self.count] = Some(obj);
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Object Cache
+/// ============================================================================
+
+/// Object constructor or destructor, given the object's memory
+pub type ObjectHook = fn(obj: *mut u8);
+
+/// Usage of one cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Bytes per object, after alignment
+    pub object_size: usize,
+
+    /// Slabs owned
+    pub slabs: usize,
+
+    /// Objects carved from those slabs
+    pub objects: usize,
+
+    /// Objects handed out
+    pub in_use: usize,
+
+    /// Free objects held in magazines
+    pub cached: usize,
+
+    /// Allocations that didn't fit and went to the heap
+    pub fallbacks: u64,
+}
+
+impl CacheStats {
+    /// Percentage of carved objects in use
+    pub fn occupancy(&self) -> usize {
+        if self.objects == 0 {
+            0
+        } else {
+            self.in_use * 100 / self.objects
+        }
+    }
+}
+
+/// A cache of fixed-size objects
+pub struct ObjectCache {
+    /// Name, for the `slab` command
+    name: &'static str,
+
+    /// Size requested for each object
+    size: usize,
+
+    /// Alignment of each object
+    align: usize,
+
+    /// Slab layout
+    geometry: Geometry,
+
+    /// Run on each object when its slab is created
+    ctor: Option<ObjectHook>,
+
+    /// Run on each object when its slab is freed
+    dtor: Option<ObjectHook>,
+
+    /// Slabs
+    depot: Mutex<Depot>,
+
+    /// Per-CPU free objects
+    magazines: [SpinMutex<Magazine>; SMP_MAX_CPUS],
+
+    /// Objects handed out
+    in_use: AtomicUsize,
+
+    /// Heap fallbacks for oversized layouts
+    fallbacks: AtomicU64,
+
+    /// Listed in the cache registry
+    registered: AtomicBool,
+}
+
+unsafe impl Sync for ObjectCache {}
+
+impl ObjectCache {
+    /// Create a cache of `size`-byte objects aligned to `align`
+    pub const fn new(name: &'static str, size: usize, align: usize) -> Self {
+        let align = if align < core::mem::align_of::<u64>() {
+            core::mem::align_of::<u64>()
+        } else {
+            align
+        };
+        Self {
+            name,
+            size,
+            align,
+            geometry: Geometry::new(size, align),
+            ctor: None,
+            dtor: None,
+            depot: Mutex::new(Depot { partial: Vec::new(), slabs: 0 }),
+            magazines: [const { SpinMutex::new(Magazine::new()) }; SMP_MAX_CPUS],
+            in_use: AtomicUsize::new(0),
+            fallbacks: AtomicU64::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// Create a cache sized for `T`
+    pub const fn for_type<T>(name: &'static str) -> Self {
+        Self::new(name, core::mem::size_of::<T>(), core::mem::align_of::<T>())
+    }
+
+    /// Create a cache sized for `Arc<T>`'s allocation: two counts, then
+    /// the value
+    pub const fn for_arc<T>(name: &'static str) -> Self {
+        let word = core::mem::size_of::<usize>();
+        let value_align = core::mem::align_of::<T>();
+        let align = if value_align > word { value_align } else { word };
+        let size = align_up(align_up(2 * word, value_align) + core::mem::size_of::<T>(), align);
+        Self::new(name, size, align)
+    }
+
+    /// Give the cache a constructor and destructor
+    pub const fn with_hooks(mut self, ctor: Option<ObjectHook>, dtor: Option<ObjectHook>) -> Self {
+        self.ctor = ctor;
+        self.dtor = dtor;
+        self
+    }
+
+    /// Name of the cache
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether objects of this cache can hold `layout`
+    pub fn fits(&self, layout: Layout) -> bool {
+        layout.size() <= self.geometry.stride && layout.align() <= self.align
+    }
+
+    /// Allocate an object
+    ///
+    /// Returns `None` when the PMM has no pages for a new slab.
+    pub fn alloc(&'static self) -> Option<NonNull<u8>> {
+        self.register();
+
+        let obj = self.local_magazine().lock().pop().or_else(|| self.refill())?;
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+        Some(obj)
+    }
+
+    /// Free an object from [`ObjectCache::alloc`]
+    ///
+    /// # Safety
+    ///
+    /// `obj` must have come from this cache, not be freed already, and be
+    /// in its constructed state.
+    pub unsafe fn free(&'static self, obj: NonNull<u8>) {
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+
+        let spilled = {
+            let mut magazine = self.local_magazine().lock();
+            match magazine.push(obj) {
+                Ok(()) => return,
+                Err(obj) => {
+                    // Full: send half of it, and `obj`, back to the slabs
+                    let mut spilled = [None; MAGAZINE_ROUNDS / 2 + 1];
+                    spilled[0] = Some(obj);
+                    for slot in spilled.iter_mut().skip(1) {
+                        *slot = magazine.pop();
+                    }
+                    spilled
+                }
+            }
+        };
+
+        let mut depot = self.depot.lock();
+        for obj in spilled.into_iter().flatten() {
+            self.depot_free(&mut depot, obj);
+        }
+    }
+
+    /// Give empty slabs and magazine contents back to the PMM
+    ///
+    /// Returns the number of slabs freed. Reclaim calls this under memory
+    /// pressure.
+    pub fn reap(&self) -> usize {
+        for magazine in self.magazines.iter() {
+            let objects: Vec<NonNull<u8>> = {
+                let mut magazine = magazine.lock();
+                core::iter::from_fn(|| magazine.pop()).collect()
+            };
+            if !objects.is_empty() {
+                let mut depot = self.depot.lock();
+                for obj in objects {
+                    self.depot_free(&mut depot, obj);
+                }
+            }
+        }
+
+        let mut depot = self.depot.lock();
+        let capacity = self.geometry.capacity;
+        let mut freed = 0;
+        depot.partial.retain(|slab| {
+            if slab.free_count() < capacity {
+                return true;
+            }
+            self.release_slab(*slab);
+            freed += 1;
+            false
+        });
+        depot.slabs -= freed;
+        freed
+    }
+
+    /// Current usage
+    pub fn stats(&self) -> CacheStats {
+        let cached = self.magazines.iter().map(|magazine| magazine.lock().count).sum();
+        let slabs = self.depot.lock().slabs;
+        CacheStats {
+            object_size: self.geometry.stride,
+            slabs,
+            objects: slabs * self.geometry.capacity,
+            in_use: self.in_use.load(Ordering::Relaxed),
+            cached,
+            fallbacks: self.fallbacks.load(Ordering::Relaxed),
+        }
+    }
+
+    /// This CPU's magazine
+    fn local_magazine(&self) -> &SpinMutex<Magazine> {
+        let cpu = (percpu::current_cpu_num() as usize).min(SMP_MAX_CPUS - 1);
+        &self.magazines[cpu]
+    }
+
+    /// Take objects from the slabs: one to return, and half a magazine
+    /// for this CPU
+    fn refill(&'static self) -> Option<NonNull<u8>> {
+        let mut batch = [None; MAGAZINE_ROUNDS / 2];
+        let obj = {
+            let mut depot = self.depot.lock();
+            let obj = self.depot_alloc(&mut depot)?;
+            for slot in batch.iter_mut() {
+                *slot = self.depot_alloc(&mut depot);
+            }
+            obj
+        };
+
+        // Another allocation on this CPU may have refilled it meanwhile
+        let mut overflow = [None; MAGAZINE_ROUNDS / 2];
+        {
+            let mut magazine = self.local_magazine().lock();
+            for (extra, slot) in batch.into_iter().flatten().zip(overflow.iter_mut()) {
+                *slot = magazine.push(extra).err();
+            }
+        }
+        if overflow.iter().any(Option::is_some) {
+            let mut depot = self.depot.lock();
+            for extra in overflow.into_iter().flatten() {
+                self.depot_free(&mut depot, extra);
+            }
+        }
+        Some(obj)
+    }
+
+    /// Take an object from a slab, growing the cache if none has room
+    fn depot_alloc(&self, depot: &mut Depot) -> Option<NonNull<u8>> {
+        if depot.partial.is_empty() {
+            let slab = self.new_slab()?;
+            depot.partial.push(slab);
+            depot.slabs += 1;
+        }
+
+        let slab = *depot.partial.last()?;
+        let obj = slab.pop(&self.geometry);
+        if slab.free_count() == 0 {
+            depot.partial.pop();
+        }
+        obj
+    }
+
+    /// Return an object to its slab, freeing the slab if it was the
+    /// second one to go empty
+    fn depot_free(&self, depot: &mut Depot, obj: NonNull<u8>) {
+        let slab = Slab::of(obj, &self.geometry);
+        debug_assert!(core::ptr::eq(slab.header().cache, self));
+
+        let was_full = slab.free_count() == 0;
+        slab.push(obj, &self.geometry);
+        if was_full {
+            depot.partial.push(slab);
+            return;
+        }
+
+        // Keep one empty slab to absorb alloc/free churn, free the rest
+        let capacity = self.geometry.capacity;
+        if slab.free_count() == capacity {
+            let empties = depot.partial.iter().filter(|s| s.free_count() == capacity).count();
+            if empties > 1 {
+                if let Some(pos) = depot.partial.iter().position(|s| *s == slab) {
+                    depot.partial.swap_remove(pos);
+                    depot.slabs -= 1;
+                    self.release_slab(slab);
+                }
+            }
+        }
+    }
+
+    /// Allocate and format a slab, constructing its objects
+    fn new_slab(&self) -> Option<Slab> {
+        let geometry = &self.geometry;
+        if geometry.capacity == 0 {
+            return None;
+        }
+
+        let align_log2 = geometry.slab_size.trailing_zeros() as u8;
+        let paddr = pmm::pmm_alloc_contiguous(geometry.pages(), pmm::PMM_ALLOC_FLAG_ANY, align_log2).ok()?;
+        let mem = crate::kernel::vm::phys_to_physmap(paddr as usize) as *mut u8;
+
+        let slab = unsafe { Slab::init(NonNull::new(mem)?, paddr, self, geometry) };
+        if let Some(ctor) = self.ctor {
+            for index in 0..geometry.capacity {
+                ctor(slab.object(index, geometry).as_ptr());
+            }
+        }
+        Some(slab)
+    }
+
+    /// Destroy an empty slab's objects and free its pages
+    fn release_slab(&self, slab: Slab) {
+        let geometry = &self.geometry;
+        if let Some(dtor) = self.dtor {
+            for index in 0..geometry.capacity {
+                dtor(slab.object(index, geometry).as_ptr());
+            }
+        }
+        pmm::pmm_free_contiguous(slab.header().paddr, geometry.pages());
+    }
+
+    /// List the cache in the registry on first use
+    fn register(&'static self) {
+        if !self.registered.swap(true, Ordering::AcqRel) {
+            CACHES.lock().push(self);
+        }
+    }
+}
+
+unsafe impl Allocator for &'static ObjectCache {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.fits(layout) {
+            self.fallbacks.fetch_add(1, Ordering::Relaxed);
+            return Global.allocate(layout);
+        }
+        let obj = (*self).alloc().ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(obj, self.size.max(layout.size())))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if !self.fits(layout) {
+            return Global.deallocate(ptr, layout);
+        }
+        (*self).free(ptr);
+    }
+}
+
+/// ============================================================================
+/// Registry
+/// ============================================================================
+
+/// Caches that have allocated
+static CACHES: Mutex<Vec<&'static ObjectCache>> = Mutex::new(Vec::new());
+
+/// Call `f` on every cache that has allocated
+pub fn for_each_cache(mut f: impl FnMut(&'static ObjectCache)) {
+    for cache in CACHES.lock().iter() {
+        f(cache);
+    }
+}
+
+/// Reap every cache, returning the number of slabs freed
+pub fn reap_all() -> usize {
+    let caches: Vec<&'static ObjectCache> = CACHES.lock().clone();
+    caches.iter().map(|cache| cache.reap()).sum()
+}
+
+/// `slab` console command
+fn cmd_slab(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    crate::println!(
+        "{:<20} {:>6} {:>6} {:>8} {:>8} {:>5} {:>7} {:>9}",
+        "cache", "size", "slabs", "objects", "in use", "occ%", "cached", "fallback"
+    );
+    for_each_cache(|cache| {
+        let stats = cache.stats();
+        crate::println!(
+            "{:<20} {:>6} {:>6} {:>8} {:>8} {:>5} {:>7} {:>9}",
+            cache.name,
+            stats.object_size,
+            stats.slabs,
+            stats.objects,
+            stats.in_use,
+            stats.occupancy(),
+            stats.cached,
+            stats.fallbacks
+        );
+    });
+    0
+}
+
+crate::static_command!("slab", "show object cache usage", cmd_slab);
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::alloc::{alloc, dealloc};
+
+    #[test]
+    fn test_geometry_fits_slab() {
+        for &(size, align) in &[(1, 8), (24, 8), (200, 64), (1000, 8), (4096, 8), (9000, 16)] {
+            let geometry = Geometry::new(size, align);
+            assert!(geometry.slab_size.is_power_of_two());
+            assert!(geometry.capacity >= MIN_OBJECTS_PER_SLAB);
+            assert_eq!(geometry.first % align, 0);
+            assert!(geometry.first + geometry.capacity * geometry.stride <= geometry.slab_size);
+        }
+    }
+
+    #[test]
+    fn test_for_arc_size() {
+        let cache = ObjectCache::for_arc::<u64>("test");
+        assert_eq!(cache.size, 24);
+        let cache = ObjectCache::for_arc::<u8>("test");
+        assert_eq!(cache.size, 24);
+    }
+
+    #[test]
+    fn test_slab_pop_push() {
+        let geometry = Geometry::new(64, 8);
+        let layout = Layout::from_size_align(geometry.slab_size, geometry.slab_size).unwrap();
+        let mem = NonNull::new(unsafe { alloc(layout) }).unwrap();
+        let slab = unsafe { Slab::init(mem, 0, core::ptr::null(), &geometry) };
+
+        let a = slab.pop(&geometry).unwrap();
+        let b = slab.pop(&geometry).unwrap();
+        assert_eq!(b.as_ptr() as usize - a.as_ptr() as usize, geometry.stride);
+        assert!(Slab::of(b, &geometry) == slab);
+        assert_eq!(slab.free_count(), geometry.capacity - 2);
+
+        slab.push(a, &geometry);
+        assert_eq!(slab.pop(&geometry), Some(a));
+
+        while slab.pop(&geometry).is_some() {}
+        assert_eq!(slab.free_count(), 0);
+
+        unsafe { dealloc(mem.as_ptr(), layout) };
+    }
+
+    #[test]
+    fn test_magazine() {
+        let mut magazine = Magazine::new();
+        let obj = NonNull::<u64>::dangling().cast::<u8>();
+        for _ in 0..MAGAZINE_ROUNDS {
+            assert!(magazine.push(obj).is_ok());
+        }
+        assert!(magazine.push(obj).is_err());
+        assert_eq!(magazine.pop(), Some(obj));
+        assert_eq!(magazine.count, MAGAZINE_ROUNDS - 1);
+    }
+}
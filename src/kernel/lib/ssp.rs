@@ -0,0 +1,99 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Stack Smashing Protection
+//!
+//! Guard values and the failure handler for code built with stack
+//! canaries (`-Z stack-protector` for Rust, `-fstack-protector*` for C).
+//!
+//! Two compiler ABIs are supported:
+//!
+//! - **TLS**: the canary is read from the thread pointer at
+//!   `RX_TLS_STACK_GUARD_OFFSET` (`%gs:0x10` on x86_64, `TPIDR_EL1 - 16`
+//!   on arm64). Every thread has its own guard in `ArchData::stack_guard`;
+//!   on x86_64 the context switch copies it into the per-CPU slot, on
+//!   arm64 the thread pointer already points into the thread.
+//! - **Global**: the canary is read from `__stack_chk_guard`, which is
+//!   set once in start.S and never changes afterwards.
+//!
+//! Both are filled as early as possible by `choose_stack_guard`, before
+//! the CPRNG is seeded. Guards for threads created later come from the
+//! CPRNG.
+
+use crate::kernel::lib::crypto;
+use crate::kernel::lib::crypto::entropy;
+
+/// Bits of every guard that are forced to zero
+///
+/// A zero low byte stops string functions from reading the guard out or
+/// writing it back during an overflow.
+pub const STACK_GUARD_MASK: u64 = !0xff;
+
+/// Boot-wide guard for code built with the global ABI
+///
+/// Written once by start.S; changing it later would trip the canary of
+/// every function already on a stack.
+#[no_mangle]
+pub static mut __stack_chk_guard: u64 = 0x595e_9fbd_94fd_a700;
+
+/// Called by instrumented code when a canary doesn't match
+#[no_mangle]
+pub extern "C" fn __stack_chk_fail() -> ! {
+    panic!("stack canary corrupted");
+}
+
+/// Choose the boot stack guard
+///
+/// Called from start.S before the CPRNG is seeded, so this reads the CPU's
+/// random number instructions directly and falls back to the cycle counter.
+/// The caller installs the result only after this returns, so this
+/// function's own canary is checked against the value it started with.
+#[no_mangle]
+pub extern "C" fn choose_stack_guard() -> u64 {
+    let mut guard = 0u64;
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(&mut guard as *mut u64 as *mut u8, 8)
+    };
+
+    if entropy::read_hw_rng(bytes) < bytes.len() {
+        // No hardware RNG: better than a constant, but only just
+        guard ^= entropy::cycle_counter().wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(29);
+        guard ^= 0xdeadbeef_00ff_00ff;
+    }
+
+    guard & STACK_GUARD_MASK
+}
+
+/// Choose a stack guard for a new thread
+pub fn new_thread_stack_guard() -> u64 {
+    crypto::rand_u64() & STACK_GUARD_MASK
+}
+
+/// Current value of the boot-wide guard
+pub fn global_stack_guard() -> u64 {
+    unsafe { core::ptr::read_volatile(core::ptr::addr_of!(__stack_chk_guard)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_stack_guard() {
+        let guard = choose_stack_guard();
+        assert_ne!(guard, 0);
+        assert_eq!(guard & !STACK_GUARD_MASK, 0);
+    }
+
+    #[test]
+    fn test_thread_stack_guards_differ() {
+        let a = new_thread_stack_guard();
+        let b = new_thread_stack_guard();
+        assert_ne!(a, b);
+        assert_eq!(a & !STACK_GUARD_MASK, 0);
+        assert_eq!(b & !STACK_GUARD_MASK, 0);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Symbolizer
+//!
+//! Maps code addresses to `function+offset` using a symbol table embedded
+//! in the kernel image.
+//!
+//! # Design
+//!
+//! The table lives in a fixed-size `.rodata.ksymtab` buffer that is empty
+//! at link time. `scripts/gen-ksymtab.py` fills it after linking from the
+//! ELF symbol table, so the table describes the final image without a
+//! second link. An image that was never post-processed still boots; its
+//! backtraces just show raw addresses.
+//!
+//! Layout (little-endian):
+//!
+//! ```text
+//! Header  { magic: u32 = "KSYM", count: u32, strtab_len: u32, reserved: u32 }
+//! Entry   { addr: u64, size: u32, name_off: u32 }   * count, sorted by addr
+//! strtab  { NUL-terminated names }                  strtab_len bytes
+//! ```
+//!
+//! Addresses are link-time addresses; lookups subtract the KASLR slide.
+
+use crate::kernel::vm::aslr;
+
+/// Table magic ("KSYM")
+pub const KSYMTAB_MAGIC: u32 = 0x4d59_534b;
+
+/// Space reserved in the image for the table
+pub const KSYMTAB_SIZE: usize = 512 * 1024;
+
+/// Header size in bytes
+const HEADER_SIZE: usize = 16;
+
+/// Entry size in bytes
+const ENTRY_SIZE: usize = 16;
+
+/// Embedded symbol table, filled in after linking
+#[no_mangle]
+#[used]
+#[link_section = ".rodata.ksymtab"]
+static KSYMTAB: [u8; KSYMTAB_SIZE] = [0; KSYMTAB_SIZE];
+
+/// A resolved symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol<'a> {
+    /// Symbol name
+    pub name: &'a str,
+
+    /// Runtime start address
+    pub addr: usize,
+
+    /// Distance of the looked-up address from `addr`
+    pub offset: usize,
+}
+
+/// A view of a symbol table
+#[derive(Clone, Copy)]
+pub struct SymbolTable<'a> {
+    entries: &'a [u8],
+    strtab: &'a [u8],
+    count: usize,
+}
+
+impl<'a> SymbolTable<'a> {
+    /// Parse a table, returning None if it is missing or malformed
+    pub fn parse(buf: &'a [u8]) -> Option<Self> {
+        if buf.len() < HEADER_SIZE || read_u32(buf, 0) != KSYMTAB_MAGIC {
+            return None;
+        }
+
+        let count = read_u32(buf, 4) as usize;
+        let strtab_len = read_u32(buf, 8) as usize;
+        let entries_len = count.checked_mul(ENTRY_SIZE)?;
+        let strtab_start = HEADER_SIZE.checked_add(entries_len)?;
+        let strtab_end = strtab_start.checked_add(strtab_len)?;
+        if strtab_end > buf.len() {
+            return None;
+        }
+
+        Some(Self {
+            entries: &buf[HEADER_SIZE..strtab_start],
+            strtab: &buf[strtab_start..strtab_end],
+            count,
+        })
+    }
+
+    /// Number of symbols
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether the table has no symbols
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Find the symbol containing the link-time address `addr`
+    pub fn lookup(&self, addr: u64) -> Option<(&'a str, u64, u64)> {
+        // Last entry starting at or below addr
+        let mut lo = 0;
+        let mut hi = self.count;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.entry_addr(mid) <= addr {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        if lo == 0 {
+            return None;
+        }
+
+        let index = lo - 1;
+        let start = self.entry_addr(index);
+        let size = read_u32(self.entries, index * ENTRY_SIZE + 8) as u64;
+        if size != 0 && addr - start >= size {
+            return None;
+        }
+
+        let name_off = read_u32(self.entries, index * ENTRY_SIZE + 12) as usize;
+        Some((self.name(name_off)?, start, addr - start))
+    }
+
+    fn entry_addr(&self, index: usize) -> u64 {
+        read_u64(self.entries, index * ENTRY_SIZE)
+    }
+
+    fn name(&self, off: usize) -> Option<&'a str> {
+        let rest = self.strtab.get(off..)?;
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        core::str::from_utf8(&rest[..len]).ok()
+    }
+}
+
+/// The kernel's own symbol table, if the image was post-processed
+pub fn kernel_symbols() -> Option<SymbolTable<'static>> {
+    // The table is patched after linking, so the compiler must not fold
+    // reads of it to the all-zero initializer
+    let table: &'static [u8; KSYMTAB_SIZE] = core::hint::black_box(&KSYMTAB);
+    SymbolTable::parse(table)
+}
+
+/// Resolve a runtime kernel address
+pub fn symbolize(addr: usize) -> Option<Symbol<'static>> {
+    let table = kernel_symbols()?;
+    let slide = aslr::kernel_slide() as usize;
+    let (name, start, offset) = table.lookup(addr.wrapping_sub(slide) as u64)?;
+
+    Some(Symbol {
+        name,
+        addr: (start as usize).wrapping_add(slide),
+        offset: offset as usize,
+    })
+}
+
+fn read_u32(buf: &[u8], off: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[off..off + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(buf: &[u8], off: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[off..off + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn build_table(symbols: &[(u64, u32, &str)]) -> Vec<u8> {
+        let mut strtab = Vec::new();
+        let mut entries = Vec::new();
+        for &(addr, size, name) in symbols {
+            entries.extend_from_slice(&addr.to_le_bytes());
+            entries.extend_from_slice(&size.to_le_bytes());
+            entries.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&KSYMTAB_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&entries);
+        buf.extend_from_slice(&strtab);
+        buf
+    }
+
+    #[test]
+    fn test_lookup() {
+        let buf = build_table(&[(0x1000, 0x20, "kmain"), (0x1040, 0, "panic"), (0x2000, 0x10, "idle")]);
+        let table = SymbolTable::parse(&buf).unwrap();
+        assert_eq!(table.len(), 3);
+
+        assert_eq!(table.lookup(0x1000), Some(("kmain", 0x1000, 0)));
+        assert_eq!(table.lookup(0x101f), Some(("kmain", 0x1000, 0x1f)));
+        // Past the end of kmain, before panic
+        assert_eq!(table.lookup(0x1030), None);
+        // Unknown size extends to the next symbol
+        assert_eq!(table.lookup(0x1fff), Some(("panic", 0x1040, 0xfbf)));
+        assert_eq!(table.lookup(0x2008), Some(("idle", 0x2000, 8)));
+        assert_eq!(table.lookup(0xfff), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_tables() {
+        assert!(SymbolTable::parse(&[0u8; 64]).is_none());
+
+        let mut buf = build_table(&[(0x1000, 0x20, "kmain")]);
+        buf.truncate(buf.len() - 2);
+        assert!(SymbolTable::parse(&buf).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Time Page
+//!
+//! A page of time values for the vDSO to map read-only into processes, so
+//! reading monotonic time or UTC doesn't need a syscall:
+//!
+//! - **Monotonic**: the hardware clock plus `reference_offset`, which only
+//!   changes when [`timer::resume`](crate::kernel::timer::resume) makes up
+//!   for a clock that stopped while suspended.
+//! - **UTC**: the system UTC clock's transform, backstop and any slew in
+//!   progress, published on every update to the clock.
+//!
+//! The values are behind a [`SeqLock`] at the start of the page. Readers in
+//! userspace follow the same protocol as [`SeqLock::read`]: read the
+//! sequence, copy the values, and retry if the sequence was odd or changed.
+//!
+//! The vDSO loader maps [`paddr`]; until it does, the kernel's own readers
+//! use [`snapshot`].
+
+use crate::kernel::mmu;
+use crate::kernel::object::clock::{ClockDetails, Transform};
+use crate::kernel::sync::seqlock::SeqLock;
+use crate::rustux::types::{PAddr, VAddr};
+
+/// Values published in the time page
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeValues {
+    /// Added to the hardware clock, in nanoseconds, to get monotonic time
+    pub reference_offset: u64,
+
+    /// Total time spent suspended, in nanoseconds
+    pub suspended_time: u64,
+
+    /// Earliest time the UTC clock reads
+    pub utc_backstop: i64,
+
+    /// UTC transform in effect
+    pub utc_transform: Transform,
+
+    /// Reference time a UTC slew in progress ends, or 0
+    pub utc_slew_end: i64,
+
+    /// UTC transform from `utc_slew_end` on
+    pub utc_after_slew: Transform,
+}
+
+impl TimeValues {
+    const fn new() -> Self {
+        Self {
+            reference_offset: 0,
+            suspended_time: 0,
+            utc_backstop: 0,
+            utc_transform: Transform::stopped(0),
+            utc_slew_end: 0,
+            utc_after_slew: Transform::stopped(0),
+        }
+    }
+
+    /// Monotonic time at hardware clock reading `hw`
+    pub fn monotonic_at(&self, hw: u64) -> u64 {
+        hw + self.reference_offset
+    }
+
+    /// UTC at monotonic time `reference`
+    pub fn utc_at(&self, reference: i64) -> i64 {
+        let transform = if self.utc_slew_end != 0 && reference >= self.utc_slew_end {
+            self.utc_after_slew
+        } else {
+            self.utc_transform
+        };
+        transform.apply(reference).max(self.utc_backstop)
+    }
+}
+
+/// The time page
+#[repr(C, align(4096))]
+pub struct TimePage {
+    /// Published values
+    pub values: SeqLock<TimeValues>,
+}
+
+static TIME_PAGE: TimePage = TimePage { values: SeqLock::new(TimeValues::new()) };
+
+/// Copy the current values out
+pub fn snapshot() -> TimeValues {
+    TIME_PAGE.values.read()
+}
+
+/// Publish the monotonic clock's offset and total suspended time
+pub fn publish_reference(offset: u64, suspended_time: u64) {
+    TIME_PAGE.values.write(|values| {
+        values.reference_offset = offset;
+        values.suspended_time = suspended_time;
+    });
+}
+
+/// Publish the system UTC clock from its details
+pub fn publish_utc(details: &ClockDetails) {
+    TIME_PAGE.values.write(|values| {
+        values.utc_backstop = details.backstop_time;
+        values.utc_transform = details.reference_to_synthetic;
+        values.utc_slew_end = details.slew_end_reference;
+        values.utc_after_slew = details.after_slew;
+    });
+}
+
+/// Physical address of the time page, for the vDSO to map
+pub fn paddr() -> Option<PAddr> {
+    mmu::virt_to_phys(&TIME_PAGE as *const TimePage as VAddr)
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_at_follows_slew() {
+        let mut values = TimeValues::new();
+        values.utc_backstop = 50;
+        assert_eq!(values.utc_at(10), 50);
+
+        values.utc_transform = Transform::at_rate(0, 1000, 500);
+        values.utc_slew_end = 2_000_000;
+        values.utc_after_slew = Transform::at_rate(2_000_000, 2_001_000, 0);
+        assert_eq!(values.utc_at(1_000_000), 1_001_500);
+        assert_eq!(values.utc_at(3_000_000), 3_001_000);
+    }
+}
@@ -176,11 +176,14 @@ impl UserbootState {
             cmdline: [0u8; CMDLINE_MAX],
         };
 
+        // The first process holds the root resource
+        let root_resource = crate::kernel::syscalls::resource::grant_root()?;
+
         // Fill in handle info
         let handle_info = &mut msg.handle_info;
         handle_info[BootstrapHandleIndex::Vdso as usize] = make_handle_info(0, 0);
         handle_info[BootstrapHandleIndex::Ramdisk as usize] = make_handle_info(1, 0);
-        handle_info[BootstrapHandleIndex::ResourceRoot as usize] = make_handle_info(2, 0);
+        handle_info[BootstrapHandleIndex::ResourceRoot as usize] = make_handle_info(2, root_resource);
         handle_info[BootstrapHandleIndex::Stack as usize] = make_handle_info(3, 0);
         handle_info[BootstrapHandleIndex::Proc as usize] = make_handle_info(4, 0);
         handle_info[BootstrapHandleIndex::Thread as usize] = make_handle_info(5, 0);
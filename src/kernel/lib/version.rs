@@ -17,7 +17,6 @@ use alloc::string::String;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::rustux::types::*;
-use crate::kernel::lib::console::{register_command, Cmd, CmdArg};
 
 /// Maximum build ID string length (SHA256 would be 64 hex chars + null)
 const MAX_BUILD_ID_STRING_LEN: usize = 65;
@@ -34,7 +33,7 @@ pub struct BuildIdNote {
     pub type_: u32,
     /// Note name (padded to 4-byte boundary)
     pub name: [u8; 8],
-    /// Build ID bytes follow
+    // Build ID bytes follow
 }
 
 /// Version information structure
@@ -57,12 +56,30 @@ pub struct VersionInfo {
     pub elf_build_id: &'static str,
 }
 
+/// Target architecture
+#[cfg(target_arch = "x86_64")]
+const ARCH: &str = "x86_64";
+#[cfg(target_arch = "aarch64")]
+const ARCH: &str = "arm64";
+#[cfg(target_arch = "riscv64")]
+const ARCH: &str = "riscv64";
+
+/// Build-time setting `name`, or `default` if the build didn't set it
+macro_rules! build_env {
+    ($name:literal, $default:expr) => {
+        match option_env!($name) {
+            Some(value) => value,
+            None => $default,
+        }
+    };
+}
+
 /// Global version information
 static VERSION_INFO: VersionInfo = VersionInfo {
     struct_version: 1,
-    arch: env!("RUSTUX_ARCH"),
-    platform: env!("RUSTUX_PLATFORM"),
-    target: env!("TARGET"),
+    arch: build_env!("RUSTUX_ARCH", ARCH),
+    platform: build_env!("RUSTUX_PLATFORM", "pc"),
+    target: build_env!("TARGET", ARCH),
     project: env!("CARGO_PKG_NAME"),
     buildid: env!("CARGO_PKG_VERSION"),
     elf_build_id: "",
@@ -71,6 +88,26 @@ static VERSION_INFO: VersionInfo = VersionInfo {
 /// ELF build ID string buffer
 static ELF_BUILD_ID_STRING: AtomicUsize = AtomicUsize::new(0);
 
+/// Kernel version
+pub fn kernel_version() -> &'static str {
+    VERSION_INFO.buildid
+}
+
+/// ELF build ID as a hex string, if it has been read
+pub fn elf_build_id() -> Option<&'static str> {
+    let build_id = ELF_BUILD_ID_STRING.load(Ordering::Acquire);
+    if build_id == 0 {
+        return None;
+    }
+    // SAFETY: The build ID string is null-terminated and never freed
+    unsafe { core::ffi::CStr::from_ptr(build_id as *const i8).to_str().ok() }
+}
+
+/// Build ID: the ELF build ID, or the kernel version if it isn't known
+pub fn build_id() -> &'static str {
+    elf_build_id().unwrap_or(VERSION_INFO.buildid)
+}
+
 /// Print version information
 pub fn print_version() {
     println!("version:");
@@ -80,17 +117,13 @@ pub fn print_version() {
     println!("\tproject:  {}", VERSION_INFO.project);
     println!("\tbuildid:  {}", VERSION_INFO.buildid);
 
-    let build_id = ELF_BUILD_ID_STRING.load(Ordering::Acquire);
-    if build_id != 0 {
-        // SAFETY: The build ID string is null-terminated
-        unsafe {
-            let s = core::ffi::CStr::from_ptr(build_id as *const i8);
-            if let Ok(str) = s.to_str() {
-                println!("\tELF build ID: {}", str);
-            }
+    match elf_build_id() {
+        Some(build_id) => {
+            println!("\tELF build ID: {}", build_id);
+        }
+        None => {
+            println!("\tELF build ID: <not available>");
         }
-    } else {
-        println!("\tELF build ID: <not available>");
     }
 }
 
@@ -106,7 +139,7 @@ pub fn init_elf_build_id() {
 }
 
 /// Version command implementation
-fn cmd_version(_argc: usize, _argv: &[CmdArg], _flags: u32) -> i32 {
+fn cmd_version(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
     print_version();
     0
 }
@@ -117,20 +150,11 @@ pub fn print_version_init() {
     print_version();
 }
 
-/// Register version commands
-pub fn version_register() {
-    register_command(Cmd {
-        name: "version",
-        help: "print version information",
-        func: Some(cmd_version),
-        flags: 0,
-    });
-}
+crate::static_command!("version", "print version information", cmd_version);
 
 /// Initialize version module
 pub fn init() {
     init_elf_build_id();
-    version_register();
 
     #[cfg(debug_assertions)]
     print_version_init();
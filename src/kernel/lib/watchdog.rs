@@ -4,343 +4,361 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT
 
-//! Watchdog Timer
+//! Kernel Watchdog
 //!
-//! This module provides watchdog timer functionality for detecting system hangs.
-
-#![no_std]
-
-extern crate alloc;
-
-use alloc::format;
-use alloc::string::String;
-use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use spin::Mutex;
-
-use crate::rustux::types::*;
-
-/// Watchdog magic number for validation
-pub const WATCHDOG_MAGIC: u32 = 0x57415447; // "WTG" in hex
-
-/// Default watchdog timeout in milliseconds
-pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
-
-/// Watchdog structure
-#[repr(C)]
-pub struct Watchdog {
-    /// Magic number for validation
-    pub magic: u32,
-    /// Watchdog name
-    pub name: String,
-    /// Whether the watchdog is enabled
-    pub enabled: bool,
-    /// Timeout in nanoseconds
-    pub timeout: u64,
-    /// Last pet time (nanoseconds since boot)
-    last_pet: AtomicU64,
-}
+//! Turns a hung kernel into a reboot with a crash log.
+//!
+//! # Design
+//!
+//! - **Kicker**: a kernel thread, just below the DPC threads in priority,
+//!   kicks the software watchdog every quarter of the timeout and pets
+//!   the hardware watchdog.
+//! - **Software watchdog**: every timer tick checks the last kick. A kick
+//!   older than the timeout means threads stopped being scheduled, and
+//!   the watchdog fires.
+//! - **Hardware watchdog**: a driver from
+//!   [`dev::watchdog`](crate::kernel::dev::watchdog), set to twice the
+//!   software timeout. It only resets the machine if the software
+//!   watchdog couldn't fire, such as when every CPU is stuck with
+//!   interrupts off.
+//!
+//! # Expiry Policy
+//!
+//! - `crashlog` (default) - panic with `HALT_REASON_SW_WATCHDOG`, which
+//!   records the crash log and reboots
+//! - `reboot` - reboot straight away
+//! - `warn` - log a warning and carry on; it fires again only after a
+//!   kick
+//!
+//! # Command Line
+//!
+//! - `kernel.watchdog=true` - Enable the watchdog (default off)
+//! - `kernel.watchdog.timeout-ms=<ms>` - Software timeout (default 10000)
+//! - `kernel.watchdog.action=crashlog|reboot|warn` - Expiry policy
+//! - `kernel.watchdog.hw=false` - Leave the hardware watchdog alone
 
-impl Watchdog {
-    /// Create a new watchdog
-    pub fn new(timeout_ms: u64, name: &str) -> Self {
-        Self {
-            magic: WATCHDOG_MAGIC,
-            name: name.to_string(),
-            enabled: false,
-            timeout: timeout_ms * 1_000_000, // Convert to nanoseconds
-            last_pet: AtomicU64::new(0),
-        }
-    }
+use crate::kernel::cmdline;
+use crate::kernel::dev::watchdog::{self as hw, HwWatchdog};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::sync::{Event, EventFlags};
+use crate::kernel::thread::{self, ThreadPriority};
+use crate::kernel::timer;
+use crate::platform;
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 
-    /// Check if the watchdog has a valid magic number
-    pub fn is_valid(&self) -> bool {
-        self.magic == WATCHDOG_MAGIC
-    }
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
 
-    /// Initialize the watchdog
-    pub fn init(&mut self, timeout_ms: u64, name: &str) {
-        self.magic = WATCHDOG_MAGIC;
-        self.name = name.to_string();
-        self.enabled = false;
-        self.timeout = timeout_ms * 1_000_000;
-    }
+// Import logging macros
+use crate::{log_error, log_info, log_warn};
 
-    /// Enable or disable the watchdog
-    pub fn set_enabled(&mut self, enabled: bool) {
-        if !self.is_valid() {
-            return;
-        }
+KCOUNTER!(WATCHDOG_KICKS, "kernel.watchdog.kicks");
+KCOUNTER!(WATCHDOG_FIRED, "kernel.watchdog.fired");
 
-        if self.enabled == enabled {
-            return;
-        }
+/// Command line switch for the watchdog
+const CMDLINE_WATCHDOG: &str = "kernel.watchdog";
 
-        self.enabled = enabled;
+/// Command line option for the software timeout
+const CMDLINE_TIMEOUT: &str = "kernel.watchdog.timeout-ms";
 
-        if enabled {
-            // Start the watchdog timer
-            self.pet();
-        } else {
-            // Cancel the watchdog timer
-            // TODO: Implement timer_cancel
-        }
-    }
+/// Command line option for the expiry policy
+const CMDLINE_ACTION: &str = "kernel.watchdog.action";
 
-    /// Pet the watchdog (reset the timer)
-    pub fn pet(&mut self) {
-        if !self.is_valid() || !self.enabled {
-            return;
-        }
+/// Command line switch for the hardware watchdog
+const CMDLINE_HW: &str = "kernel.watchdog.hw";
 
-        // Update last pet time
-        self.last_pet.store(get_current_time_ns(), Ordering::Release);
+/// Default software timeout
+pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
 
-        // Reset the timer
-        // TODO: Reset hardware watchdog timer
-    }
+/// Shortest software timeout
+pub const MIN_TIMEOUT_MS: u64 = 100;
 
-    /// Check if the watchdog has expired
-    pub fn check_expired(&self) -> bool {
-        if !self.is_valid() || !self.enabled {
-            return false;
-        }
+/// Kicker thread priority
+const KICKER_PRIORITY: ThreadPriority = thread::PRIORITY_REALTIME - 2;
 
-        let now = get_current_time_ns();
-        let last_pet = self.last_pet.load(Ordering::Acquire);
+/// What to do when the software watchdog fires
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Panic, recording the crash log, then reboot
+    Crashlog = 0,
 
-        // Check if timeout has elapsed
-        now.saturating_sub(last_pet) > self.timeout
-    }
-}
+    /// Reboot without a crash log
+    Reboot = 1,
 
-/// Hardware watchdog state
-struct HwWatchdogState {
-    /// Whether hardware watchdog is enabled
-    enabled: bool,
-    /// Pet timeout (nanoseconds)
-    pet_timeout: u64,
-    /// Last pet time
-    last_pet: AtomicU64,
+    /// Log and carry on
+    Warn = 2,
 }
 
-/// Global hardware watchdog state
-static HW_WATCHDOG: Mutex<HwWatchdogState> = Mutex::new(HwWatchdogState {
-    enabled: false,
-    pet_timeout: 1_000_000_000, // 1 second default
-    last_pet: AtomicU64::new(0),
-});
+impl Action {
+    /// Parse a `kernel.watchdog.action` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "crashlog" => Some(Self::Crashlog),
+            "reboot" => Some(Self::Reboot),
+            "warn" => Some(Self::Warn),
+            _ => None,
+        }
+    }
 
-/// Get current time in nanoseconds
-///
-/// TODO: Implement proper time source
-fn get_current_time_ns() -> u64 {
-    // For now, return 0
-    // In real implementation, this would read from a time source
-    0
+    const fn from_raw(raw: u8) -> Self {
+        match raw {
+            1 => Self::Reboot,
+            2 => Self::Warn,
+            _ => Self::Crashlog,
+        }
+    }
 }
 
-/// Watchdog handler callback
-///
-/// This function is called when a watchdog expires.
-/// The default implementation halts the system.
-pub fn watchdog_handler(watchdog: &Watchdog) {
-    println!(
-        "Watchdog \"{}\" (timeout {} ms) just fired!!",
-        watchdog.name,
-        watchdog.timeout / 1_000_000
-    );
+/// The timer tick checks kicks
+static ARMED: AtomicBool = AtomicBool::new(false);
 
-    // Halt the system
-    // TODO: platform_halt(HALT_ACTION_HALT, HALT_REASON_SW_RESET);
-    panic!("Watchdog expired");
-}
+/// The kicker kicks; cleared to test the watchdog
+static KICKING: AtomicBool = AtomicBool::new(true);
 
-/// Initialize hardware watchdog
-///
-/// # Arguments
-///
-/// * `timeout_ns` - Timeout in nanoseconds
-pub fn watchdog_hw_init(timeout_ns: u64) -> Result<(), i32> {
-    assert!(timeout_ns != 0);
+/// Set when the watchdog fires, until the next kick
+static FIRED: AtomicBool = AtomicBool::new(false);
+
+/// Software timeout in nanoseconds
+static TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_MS * 1_000_000);
+
+/// Time of the last kick
+static LAST_KICK: AtomicU64 = AtomicU64::new(0);
 
-    let mut state = HW_WATCHDOG.lock();
-    state.enabled = false;
-    state.pet_timeout = timeout_ns;
-    state.last_pet.store(get_current_time_ns(), Ordering::Release);
+/// `Action` on expiry
+static ACTION: AtomicU8 = AtomicU8::new(Action::Crashlog as u8);
 
-    // TODO: platform_watchdog_init(timeout_ns, &state.pet_timeout);
+/// Hardware watchdog and its timeout, if one is running
+static HW_WATCHDOG: SpinMutex<Option<(Box<dyn HwWatchdog>, u64)>> = SpinMutex::new(None);
 
-    Ok(())
+/// Wakes the kicker early
+static KICKER_EVENT: Event = Event::new(false, EventFlags::auto_unsignal());
+
+/// ============================================================================
+/// Software Watchdog
+/// ============================================================================
+
+/// Record progress
+///
+/// Called by the kicker thread; also clears a `warn` expiry.
+pub fn kick() {
+    LAST_KICK.store(timer::current_time(), Ordering::Release);
+    FIRED.store(false, Ordering::Release);
+    WATCHDOG_KICKS.add(1);
 }
 
-/// Enable or disable hardware watchdog
-pub fn watchdog_hw_set_enabled(enabled: bool) {
-    let mut state = HW_WATCHDOG.lock();
+/// Whether `now` is past the timeout since `last_kick`
+fn is_expired(now: u64, last_kick: u64, timeout: u64) -> bool {
+    now.saturating_sub(last_kick) > timeout
+}
 
-    if state.enabled == enabled {
+/// Check for a stale kick
+///
+/// Called from the timer interrupt on every CPU, so the common case is two
+/// loads.
+#[inline]
+pub fn check(now: u64) {
+    if !ARMED.load(Ordering::Relaxed) {
         return;
     }
-
-    state.enabled = enabled;
-
-    if enabled {
-        // Start hardware watchdog
-        state.last_pet.store(get_current_time_ns(), Ordering::Release);
-        // TODO: platform_watchdog_pet();
-    } else {
-        // Stop hardware watchdog
-        // TODO: platform_watchdog_disable();
+    let last_kick = LAST_KICK.load(Ordering::Acquire);
+    if is_expired(now, last_kick, TIMEOUT.load(Ordering::Relaxed)) {
+        expire(now - last_kick);
     }
 }
 
-/// Pet the hardware watchdog
-pub fn watchdog_hw_pet() {
-    let state = HW_WATCHDOG.lock();
-
-    if !state.enabled {
+#[cold]
+fn expire(stale: u64) {
+    // One CPU handles each expiry
+    if FIRED.swap(true, Ordering::AcqRel) {
         return;
     }
+    WATCHDOG_FIRED.add(1);
+
+    let stale_ms = stale / 1_000_000;
+    match Action::from_raw(ACTION.load(Ordering::Relaxed)) {
+        Action::Crashlog => crate::kernel::panic::panic_for_reason(
+            platform::HALT_REASON_SW_WATCHDOG,
+            format_args!("watchdog: no progress for {} ms", stale_ms),
+        ),
+        Action::Reboot => {
+            crate::kernel::debug::print_internal("watchdog: no progress, rebooting\n");
+            platform::platform_halt(platform::HALT_REASON_SW_WATCHDOG, platform::HALT_ACTION_REBOOT);
+        }
+        Action::Warn => {
+            log_warn!("watchdog: no progress for {} ms", stale_ms);
+        }
+    }
+}
 
-    // Update last pet time
-    state.last_pet.store(get_current_time_ns(), Ordering::Release);
+/// ============================================================================
+/// Kicker
+/// ============================================================================
 
-    // Pet the hardware watchdog
-    // TODO: platform_watchdog_pet();
+fn pet_hardware() {
+    if let Some((wdt, _)) = HW_WATCHDOG.lock().as_ref() {
+        wdt.pet();
+    }
 }
 
-/// Hardware watchdog timer callback
-///
-/// This function is called periodically to pet the hardware watchdog.
-pub fn hw_watchdog_timer_callback() {
-    let pet_timeout = {
-        let state = HW_WATCHDOG.lock();
-        if !state.enabled {
+extern "C" fn kicker_entry(_arg: usize) -> ! {
+    loop {
+        if KICKING.load(Ordering::Acquire) {
+            kick();
+            pet_hardware();
+        }
+        let interval = TIMEOUT.load(Ordering::Relaxed) / 4;
+        let _ = KICKER_EVENT.wait_deadline(timer::current_time() + interval);
+    }
+}
+
+/// Find and start the hardware watchdog at twice the software timeout
+fn start_hardware(timeout: u64) {
+    let wdt = match hw::probe() {
+        Some(wdt) => wdt,
+        None => {
+            log_info!("watchdog: no hardware watchdog");
             return;
         }
-        state.pet_timeout
     };
 
-    // Schedule next callback
-    // TODO: timer_set_oneshot(get_current_time_ns() + pet_timeout, hw_watchdog_timer_callback, nullptr);
-
-    // Pet the hardware watchdog
-    watchdog_hw_pet();
-}
-
-/// Watchdog manager for managing multiple watchdogs
-pub struct WatchdogManager {
-    /// List of registered watchdogs
-    watchdogs: Vec<Arc<Mutex<Watchdog>>>,
-}
+    if wdt.caused_last_reset() {
+        log_warn!("watchdog: {} reset the machine on the previous boot", wdt.name());
+    }
 
-impl WatchdogManager {
-    /// Create a new watchdog manager
-    pub fn new() -> Self {
-        Self {
-            watchdogs: Vec::new(),
+    let wanted = timeout.saturating_mul(2).min(wdt.max_timeout());
+    match wdt.enable(wanted) {
+        Ok(actual) => {
+            log_info!("watchdog: {} armed, {} ms", wdt.name(), actual / 1_000_000);
+            *HW_WATCHDOG.lock() = Some((wdt, actual));
+        }
+        Err(err) => {
+            log_error!("watchdog: {} failed to start: {:?}", wdt.name(), err);
         }
     }
+}
 
-    /// Add a watchdog to the manager
-    pub fn add(&mut self, watchdog: Arc<Mutex<Watchdog>>) {
-        self.watchdogs.push(watchdog);
+/// Start the watchdog if `kernel.watchdog` is set
+///
+/// Needs the scheduler, and PCI enumeration and the firmware tables for
+/// the hardware watchdog.
+pub fn init() {
+    if !cmdline::cmdline_get_bool(CMDLINE_WATCHDOG, false) {
+        return;
     }
 
-    /// Check all watchdogs and handle any that have expired
-    pub fn check_watchdogs(&self) {
-        for watchdog in &self.watchdogs {
-            let w = watchdog.lock();
-            if w.check_expired() {
-                // Call the handler
-                watchdog_handler(&w);
+    let timeout_ms = cmdline::cmdline_get_uint64(CMDLINE_TIMEOUT, DEFAULT_TIMEOUT_MS).max(MIN_TIMEOUT_MS);
+    let timeout = timeout_ms * 1_000_000;
+    TIMEOUT.store(timeout, Ordering::Relaxed);
+
+    if let Some(value) = cmdline::cmdline_get(CMDLINE_ACTION) {
+        match Action::parse(value) {
+            Some(action) => ACTION.store(action as u8, Ordering::Relaxed),
+            None => {
+                log_warn!("watchdog: ignoring unknown {}={}", CMDLINE_ACTION, value);
             }
         }
     }
 
-    /// Pet all watchdogs
-    pub fn pet_all(&self) {
-        for watchdog in &self.watchdogs {
-            let mut w = watchdog.lock();
-            w.pet();
+    kick();
+    match thread::Thread::new_kernel(kicker_entry, 0, KICKER_PRIORITY) {
+        Ok(kicker) => {
+            kicker.set_name("watchdog");
+            if let Err(err) = kicker.start() {
+                log_error!("watchdog: kicker failed to start: {:?}", err);
+                return;
+            }
+            thread::register_thread(kicker.into_ref());
+        }
+        Err(err) => {
+            log_error!("watchdog: failed to create kicker: {:?}", err);
+            return;
         }
     }
-}
+    ARMED.store(true, Ordering::Release);
 
-impl Default for WatchdogManager {
-    fn default() -> Self {
-        Self::new()
+    if cmdline::cmdline_get_bool(CMDLINE_HW, true) {
+        start_hardware(timeout);
     }
+
+    log_info!(
+        "watchdog: {} ms, on expiry {:?}",
+        timeout_ms,
+        Action::from_raw(ACTION.load(Ordering::Relaxed))
+    );
 }
 
-/// Global watchdog manager
-static WATCHDOG_MANAGER: Mutex<WatchdogManager> = Mutex::new(WatchdogManager::new());
+/// ============================================================================
+/// Shell Command
+/// ============================================================================
+
+fn cmd_watchdog(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    if argc >= 2 {
+        match argv[1] {
+            "test" => {
+                // The software watchdog fires within a timeout
+                crate::println!("watchdog: no longer kicking");
+                KICKING.store(false, Ordering::Release);
+                return 0;
+            }
+            "test-hw" => {
+                // Only the hardware watchdog is left to notice
+                crate::println!("watchdog: no longer kicking or checking");
+                ARMED.store(false, Ordering::Release);
+                KICKING.store(false, Ordering::Release);
+                return 0;
+            }
+            _ => {
+                crate::println!("usage: watchdog [test|test-hw]");
+                return -1;
+            }
+        }
+    }
 
-/// Add a watchdog to the global manager
-pub fn watchdog_register(watchdog: Arc<Mutex<Watchdog>>) {
-    WATCHDOG_MANAGER.lock().add(watchdog);
-}
+    if !ARMED.load(Ordering::Acquire) {
+        crate::println!("watchdog: off (boot with {}=true)", CMDLINE_WATCHDOG);
+        return 0;
+    }
 
-/// Check all registered watchdogs
-pub fn watchdog_check_all() {
-    WATCHDOG_MANAGER.lock().check_watchdogs();
+    let now = timer::current_time();
+    crate::println!(
+        "timeout {} ms, last kick {} ms ago, on expiry {:?}, fired {} times",
+        TIMEOUT.load(Ordering::Relaxed) / 1_000_000,
+        now.saturating_sub(LAST_KICK.load(Ordering::Acquire)) / 1_000_000,
+        Action::from_raw(ACTION.load(Ordering::Relaxed)),
+        WATCHDOG_FIRED.value()
+    );
+    match HW_WATCHDOG.lock().as_ref() {
+        Some((wdt, timeout)) => {
+            crate::println!("hardware: {}, {} ms", wdt.name(), timeout / 1_000_000);
+        }
+        None => {
+            crate::println!("hardware: none");
+        }
+    }
+    0
 }
 
-/// Pet all registered watchdogs
-pub fn watchdog_pet_all() {
-    WATCHDOG_MANAGER.lock().pet_all();
-}
+crate::static_command!("watchdog", "watchdog status, or watchdog test|test-hw", cmd_watchdog);
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_watchdog_create() {
-        let mut wd = Watchdog::new(5000, "test");
-        assert!(wd.is_valid());
-        assert_eq!(wd.name, "test");
-        assert_eq!(wd.timeout, 5_000_000_000);
-        assert!(!wd.enabled);
-    }
-
-    #[test]
-    fn test_watchdog_enable_disable() {
-        let mut wd = Watchdog::new(5000, "test");
-        assert!(!wd.enabled);
-
-        wd.set_enabled(true);
-        assert!(wd.enabled);
-
-        wd.set_enabled(false);
-        assert!(!wd.enabled);
-    }
-
-    #[test]
-    fn test_watchdog_pet() {
-        let mut wd = Watchdog::new(5000, "test");
-        wd.set_enabled(true);
-
-        // Pet the watchdog
-        wd.pet();
-
-        // Check that it hasn't expired
-        assert!(!wd.check_expired());
+    fn test_action_parse() {
+        assert_eq!(Action::parse("crashlog"), Some(Action::Crashlog));
+        assert_eq!(Action::parse("warn"), Some(Action::Warn));
+        assert_eq!(Action::parse("halt"), None);
+        assert_eq!(Action::from_raw(Action::Reboot as u8), Action::Reboot);
     }
 
     #[test]
-    fn test_watchdog_manager() {
-        let mut manager = WatchdogManager::new();
-        let wd = Arc::new(Mutex::new(Watchdog::new(5000, "test1")));
-        let wd2 = Arc::new(Mutex::new(Watchdog::new(10000, "test2")));
-
-        manager.add(wd.clone());
-        manager.add(wd2.clone());
-
-        // Pet all watchdogs
-        manager.pet_all();
-
-        // Check none have expired
-        manager.check_watchdogs();
+    fn test_is_expired() {
+        assert!(!is_expired(1_000, 500, 1_000));
+        assert!(is_expired(2_000, 500, 1_000));
+        // A kick racing the check is never stale
+        assert!(!is_expired(500, 1_000, 1_000));
     }
 }
@@ -45,15 +45,23 @@ pub mod dev;
 // Note: This is a minimal module declaration to allow building.
 // The full module structure needs to be completed.
 pub mod allocator;
+pub mod boot;
 pub mod cmdline;
 pub mod debug;
 pub mod dpc;
+pub mod handoff;
 pub mod hypervisor;
 pub mod init;
+pub mod irq;
+pub mod kasan;
 pub mod mp;
+pub mod numa;
 pub mod object;
+pub mod panic;
 pub mod percpu;
 pub mod pmm;
+pub mod power;
+pub mod preempt;
 pub mod process;
 pub mod sched;
 pub mod sync;
@@ -62,6 +70,7 @@ pub mod thread;
 pub mod timer;
 pub mod usercopy;
 pub mod vm;
+pub mod workqueue;
 
 // Re-export usercopy as user_copy for compatibility
 pub use usercopy as user_copy;
@@ -76,6 +85,10 @@ pub mod cpu;
 pub mod align;
 pub mod spinlock;
 
+// In-kernel test suites and the boot-time test runner
+#[cfg(feature = "ktest")]
+pub mod tests;
+
 // Re-export vm submodules for compatibility (if they exist)
 pub use vm::arch_vm_aspace;
 
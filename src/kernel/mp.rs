@@ -282,8 +282,11 @@ pub fn mp_is_cpu_active(cpu: u32) -> bool {
 
 /// Set current CPU as online
 pub fn mp_set_curr_cpu_online(online: bool) {
-    let cpu = percpu::current_cpu_num();
+    mp_set_cpu_online(percpu::current_cpu_num(), online);
+}
 
+/// Set a CPU as online
+pub fn mp_set_cpu_online(cpu: u32, online: bool) {
     unsafe {
         let mask = cpu_num_to_mask(cpu);
 
@@ -297,8 +300,11 @@ pub fn mp_set_curr_cpu_online(online: bool) {
 
 /// Set current CPU as active
 pub fn mp_set_curr_cpu_active(active: bool) {
-    let cpu = percpu::current_cpu_num();
+    mp_set_cpu_active(percpu::current_cpu_num(), active);
+}
 
+/// Set a CPU as active (taking reschedule IPIs)
+pub fn mp_set_cpu_active(cpu: u32, active: bool) {
     unsafe {
         let mask = cpu_num_to_mask(cpu);
 
@@ -576,34 +582,38 @@ pub fn mp_unplug_cpu_mask(cpu_mask: CpuMask) -> Result {
         return Err(RX_ERR_BAD_STATE);
     }
 
-    // Take down each CPU
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(target_arch = "riscv64")]
     {
-        let mut mask = cpu_mask;
-        while mask != 0 {
-            let cpu_id = highest_cpu_set(mask);
-            mask &= !cpu_num_to_mask(cpu_id);
+        let _ = cpu_mask; // Suppress unused warning
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
 
-            // Shutdown DPCs for this CPU
-            crate::kernel::dpc::dpc_shutdown_cpu(cpu_id);
+    // Take down each CPU
+    let mut mask = cpu_mask;
+    while mask != 0 {
+        let cpu_id = highest_cpu_set(mask);
+        mask &= !cpu_num_to_mask(cpu_id);
+
+        // Shutdown DPCs for this CPU
+        crate::kernel::dpc::dpc_shutdown_cpu(cpu_id);
 
-            // Call platform-specific unplug
+        // Call platform-specific unplug, which moves the CPU's threads away
+        #[cfg(target_arch = "aarch64")]
+        {
             let result = crate::arch::arm64::mp::arm64_mp_cpu_unplug(cpu_id);
             if result != RX_OK {
                 return Err(result);
             }
-
-            // Mark CPU as offline
-            unsafe {
-                get_state().online_cpus.fetch_and(!cpu_num_to_mask(cpu_id), Ordering::Release);
-            }
         }
-    }
 
-    #[cfg(not(target_arch = "aarch64"))]
-    {
-        let _ = cpu_mask; // Suppress unused warning
-        return Err(RX_ERR_NOT_SUPPORTED);
+        // x86 needs nothing from the platform until the CPU parks itself
+        #[cfg(target_arch = "x86_64")]
+        crate::kernel::sched::migrate_from_cpu(cpu_id);
+
+        // Mark CPU as offline
+        unsafe {
+            get_state().online_cpus.fetch_and(!cpu_num_to_mask(cpu_id), Ordering::Release);
+        }
     }
 
     Ok(())
@@ -0,0 +1,416 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! NUMA Topology
+//!
+//! Records which node each CPU and each range of physical memory belongs
+//! to, from the ACPI SRAT or from `numa-node-id` properties in the device
+//! tree. A machine that describes neither is a single node 0 holding
+//! every CPU and every page.
+//!
+//! # Design
+//!
+//! - **Nodes**: Firmware proximity domains and `numa-node-id`s can be
+//!   sparse, so they are numbered 0.. in the order they first appear, up
+//!   to [`MAX_NUMA_NODES`]. Domains past the limit fold into node 0.
+//! - **CPUs**: Looked up by hardware ID (APIC ID, MPIDR or hart ID), the
+//!   `cpu_id` in each CPU's [`PerCpu`](crate::kernel::percpu::PerCpu).
+//! - **Memory**: The PMM splits its arenas at node boundaries
+//!   ([`split_range`]), so every arena, and every page in it, is on one
+//!   node. Memory the firmware doesn't mention is on node 0.
+//! - **Policies**: A [`NumaPolicy`] on a VMO picks the node for each page
+//!   it commits; the scheduler prefers a thread's home node (see
+//!   [`sched`](crate::kernel::sched)).
+//!
+//! # Usage
+//!
+//! ```rust
+//! numa::init();
+//! let node = numa::current_node();
+//! let paddr = pmm::pmm_alloc_page_on_node(node, pmm::PMM_ALLOC_FLAG_ANY)?;
+//! ```
+
+use crate::kernel::percpu;
+use crate::kernel::sync::spin::SpinMutex;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// Import logging macros
+use crate::log_info;
+
+/// Maximum NUMA nodes
+pub const MAX_NUMA_NODES: usize = 8;
+
+/// "No particular node": allocate anywhere, or run anywhere
+pub const NUMA_NODE_ANY: u32 = u32::MAX;
+
+/// Maximum memory ranges recorded
+const MAX_NUMA_RANGES: usize = 32;
+
+/// Maximum CPUs recorded
+const MAX_NUMA_CPUS: usize = percpu::SMP_MAX_CPUS;
+
+/// ============================================================================
+/// Topology
+/// ============================================================================
+
+/// A physical memory range on one node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeRange {
+    /// Physical base address
+    pub base: u64,
+
+    /// Length in bytes
+    pub length: u64,
+
+    /// Node
+    pub node: u32,
+}
+
+impl NodeRange {
+    const EMPTY: Self = Self { base: 0, length: 0, node: 0 };
+
+    fn end(&self) -> u64 {
+        self.base.saturating_add(self.length)
+    }
+}
+
+/// Which node each CPU and memory range is on
+pub struct Topology {
+    /// Firmware domain of each node
+    domains: [u32; MAX_NUMA_NODES],
+    node_count: usize,
+
+    /// Memory ranges, sorted by base
+    ranges: [NodeRange; MAX_NUMA_RANGES],
+    range_count: usize,
+
+    /// (hardware CPU ID, node) pairs
+    cpus: [(u64, u32); MAX_NUMA_CPUS],
+    cpu_count: usize,
+}
+
+impl Topology {
+    /// A topology with no affinity information
+    pub const fn new() -> Self {
+        Self {
+            domains: [0; MAX_NUMA_NODES],
+            node_count: 0,
+            ranges: [NodeRange::EMPTY; MAX_NUMA_RANGES],
+            range_count: 0,
+            cpus: [(0, 0); MAX_NUMA_CPUS],
+            cpu_count: 0,
+        }
+    }
+
+    /// The node for firmware `domain`, numbering it if it is new
+    fn node_for_domain(&mut self, domain: u32) -> u32 {
+        if let Some(node) = self.domains[..self.node_count].iter().position(|&d| d == domain) {
+            return node as u32;
+        }
+        if self.node_count == MAX_NUMA_NODES {
+            return 0;
+        }
+        self.domains[self.node_count] = domain;
+        self.node_count += 1;
+        (self.node_count - 1) as u32
+    }
+
+    /// Record that `[base, base + length)` is in firmware `domain`
+    pub fn add_memory(&mut self, domain: u32, base: u64, length: u64) {
+        let node = self.node_for_domain(domain);
+        if length == 0 || self.range_count == MAX_NUMA_RANGES {
+            return;
+        }
+        let at = self.ranges[..self.range_count].partition_point(|r| r.base < base);
+        self.ranges.copy_within(at..self.range_count, at + 1);
+        self.ranges[at] = NodeRange { base, length, node };
+        self.range_count += 1;
+    }
+
+    /// Record that the CPU with hardware ID `hw_id` is in firmware `domain`
+    pub fn add_cpu(&mut self, domain: u32, hw_id: u64) {
+        let node = self.node_for_domain(domain);
+        if self.cpu_count < MAX_NUMA_CPUS {
+            self.cpus[self.cpu_count] = (hw_id, node);
+            self.cpu_count += 1;
+        }
+    }
+
+    /// Number of nodes, at least 1
+    pub fn node_count(&self) -> u32 {
+        self.node_count.max(1) as u32
+    }
+
+    /// Firmware domain of `node`
+    pub fn domain(&self, node: u32) -> Option<u32> {
+        self.domains[..self.node_count].get(node as usize).copied()
+    }
+
+    /// Memory ranges, sorted by base
+    pub fn ranges(&self) -> &[NodeRange] {
+        &self.ranges[..self.range_count]
+    }
+
+    /// Node of the CPU with hardware ID `hw_id`
+    pub fn cpu_node(&self, hw_id: u64) -> u32 {
+        self.cpus[..self.cpu_count]
+            .iter()
+            .find(|&&(id, _)| id == hw_id)
+            .map_or(0, |&(_, node)| node)
+    }
+
+    /// Node of physical address `paddr`
+    pub fn paddr_node(&self, paddr: u64) -> u32 {
+        self.ranges()
+            .iter()
+            .find(|r| paddr >= r.base && paddr < r.end())
+            .map_or(0, |r| r.node)
+    }
+
+    /// Split `[base, base + length)` at node boundaries, calling `f` with
+    /// each piece as (base, length, node)
+    ///
+    /// Pieces the topology doesn't cover are on node 0.
+    pub fn split_range(&self, base: u64, length: u64, mut f: impl FnMut(u64, u64, u32)) {
+        let end = base.saturating_add(length);
+        let mut at = base;
+        while at < end {
+            let covering = self.ranges().iter().find(|r| at >= r.base && at < r.end());
+            let (piece_end, node) = match covering {
+                Some(range) => (range.end().min(end), range.node),
+                None => {
+                    // Up to the next range that starts inside the request
+                    let next = self.ranges().iter().map(|r| r.base).find(|&b| b > at).unwrap_or(end);
+                    (next.min(end), 0)
+                }
+            };
+            f(at, piece_end - at, node);
+            at = piece_end;
+        }
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The system topology
+static TOPOLOGY: SpinMutex<Topology> = SpinMutex::new(Topology::new());
+
+/// Cached `TOPOLOGY.node_count()`
+static NODE_COUNT: AtomicU32 = AtomicU32::new(1);
+
+/// ============================================================================
+/// Initialization
+/// ============================================================================
+
+/// Build the topology from the ACPI SRAT, or else from the device tree
+///
+/// Call after ACPI and the device tree are parsed. Arenas the PMM already
+/// holds are relabelled with their node.
+pub fn init() {
+    let mut topology = Topology::new();
+
+    if let Some(srat) = crate::kernel::dev::acpi::srat() {
+        for range in srat.memory() {
+            topology.add_memory(range.domain, range.base, range.length);
+        }
+        for cpu in srat.cpus() {
+            topology.add_cpu(cpu.domain, cpu.apic_id as u64);
+        }
+    } else if let Some(info) = crate::kernel::dev::fdt::platform_info() {
+        for (base, length, node) in info.memory_numa() {
+            topology.add_memory(node, base, length);
+        }
+        for &(hw_id, node) in info.cpu_numa() {
+            topology.add_cpu(node, hw_id);
+        }
+    }
+
+    let nodes = topology.node_count();
+    log_info!("NUMA: {} node(s), {} memory range(s)", nodes, topology.ranges().len());
+    for range in topology.ranges() {
+        log_info!("  node {}: {:#x}-{:#x}", range.node, range.base, range.end());
+    }
+
+    *TOPOLOGY.lock() = topology;
+    NODE_COUNT.store(nodes, Ordering::Release);
+
+    crate::kernel::pmm::pmm_update_arena_nodes();
+}
+
+/// ============================================================================
+/// Queries
+/// ============================================================================
+
+/// Number of NUMA nodes, 1 on a machine without affinity information
+pub fn node_count() -> u32 {
+    NODE_COUNT.load(Ordering::Acquire)
+}
+
+/// Node of CPU number `cpu`
+pub fn cpu_node(cpu: u32) -> u32 {
+    if node_count() == 1 || cpu as usize >= percpu::SMP_MAX_CPUS {
+        return 0;
+    }
+    let hw_id = unsafe { percpu::get_percpu(cpu as usize).cpu_id } as u64;
+    TOPOLOGY.lock().cpu_node(hw_id)
+}
+
+/// Node of the calling CPU
+pub fn current_node() -> u32 {
+    cpu_node(percpu::current_cpu_num())
+}
+
+/// Node of physical address `paddr`
+pub fn paddr_node(paddr: u64) -> u32 {
+    if node_count() == 1 {
+        return 0;
+    }
+    TOPOLOGY.lock().paddr_node(paddr)
+}
+
+/// Split a physical range at node boundaries; see [`Topology::split_range`]
+pub fn split_range(base: u64, length: u64, f: impl FnMut(u64, u64, u32)) {
+    TOPOLOGY.lock().split_range(base, length, f)
+}
+
+/// Mask of the CPUs on `node`, among the first 64
+pub fn node_cpu_mask(node: u32) -> u64 {
+    (0..percpu::num_cpus().min(64))
+        .filter(|&cpu| cpu_node(cpu) == node)
+        .fold(0, |mask, cpu| mask | 1 << cpu)
+}
+
+/// ============================================================================
+/// Allocation Policy
+/// ============================================================================
+
+/// Which node a VMO's pages are committed on
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumaPolicy {
+    /// The node of the CPU that commits the page
+    #[default]
+    Local = 0,
+
+    /// Round-robin over every node by page offset, so a large shared
+    /// buffer spreads its bandwidth across all memory controllers
+    Interleave = 1,
+}
+
+impl NumaPolicy {
+    /// Create from a raw value passed by userspace, rejecting unknown values
+    pub const fn try_from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Local),
+            1 => Some(Self::Interleave),
+            _ => None,
+        }
+    }
+
+    /// Get raw value
+    pub const fn into_raw(self) -> u32 {
+        self as u32
+    }
+
+    /// Node for the page at `page_index` of a VMO, given `nodes` nodes and
+    /// the committing CPU's node `local`
+    ///
+    /// [`NUMA_NODE_ANY`] on a single-node machine, so the PMM can serve the
+    /// page from anywhere, including the pre-zeroed pool.
+    pub fn node_for_page(self, page_index: usize, nodes: u32, local: u32) -> u32 {
+        if nodes <= 1 {
+            return NUMA_NODE_ANY;
+        }
+        match self {
+            Self::Local => local,
+            Self::Interleave => (page_index % nodes as usize) as u32,
+        }
+    }
+
+    /// [`node_for_page`](Self::node_for_page) on this machine
+    pub fn node_for(self, page_index: usize) -> u32 {
+        let nodes = node_count();
+        if nodes <= 1 {
+            return NUMA_NODE_ANY;
+        }
+        self.node_for_page(page_index, nodes, current_node())
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn two_nodes() -> Topology {
+        let mut t = Topology::new();
+        // Domains are sparse and appear out of address order
+        t.add_memory(7, 0x1_0000_0000, 0x1_0000_0000);
+        t.add_memory(3, 0, 0x8000_0000);
+        t.add_cpu(3, 0);
+        t.add_cpu(7, 0x10);
+        t
+    }
+
+    #[test]
+    fn test_domains_numbered_in_order_seen() {
+        let t = two_nodes();
+        assert_eq!(t.node_count(), 2);
+        assert_eq!(t.domain(0), Some(7));
+        assert_eq!(t.domain(1), Some(3));
+        assert_eq!(t.ranges()[0].base, 0);
+        assert_eq!(t.ranges()[0].node, 1);
+
+        assert_eq!(t.cpu_node(0x10), 0);
+        assert_eq!(t.cpu_node(0), 1);
+        assert_eq!(t.paddr_node(0x1_2345_0000), 0);
+        assert_eq!(t.paddr_node(0x4000_0000), 1);
+        // A hole belongs to node 0
+        assert_eq!(t.paddr_node(0x9000_0000), 0);
+        assert_eq!(Topology::new().node_count(), 1);
+    }
+
+    #[test]
+    fn test_split_range() {
+        let t = two_nodes();
+        let mut pieces = Vec::new();
+        t.split_range(0x7000_0000, 0x9800_0000, |base, len, node| pieces.push((base, len, node)));
+        assert_eq!(
+            pieces,
+            [
+                (0x7000_0000, 0x1000_0000, 1),
+                (0x8000_0000, 0x8000_0000, 0),
+                (0x1_0000_0000, 0x800_0000, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_too_many_domains_fold_into_node_0() {
+        let mut t = Topology::new();
+        for domain in 0..MAX_NUMA_NODES as u32 + 2 {
+            t.add_cpu(domain, domain as u64);
+        }
+        assert_eq!(t.node_count(), MAX_NUMA_NODES as u32);
+        assert_eq!(t.cpu_node(MAX_NUMA_NODES as u64 + 1), 0);
+    }
+
+    #[test]
+    fn test_policy_node_for_page() {
+        assert_eq!(NumaPolicy::Interleave.node_for_page(5, 1, 0), NUMA_NODE_ANY);
+        assert_eq!(NumaPolicy::Local.node_for_page(5, 4, 2), 2);
+        assert_eq!(NumaPolicy::Interleave.node_for_page(5, 4, 2), 1);
+        assert_eq!(NumaPolicy::try_from_raw(2), None);
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Bus Transaction Initiator Objects
+//!
+//! A BTI stands for one device behind an IOMMU. Pinning a VMO range
+//! through it pins the pages in the VMO (see [`Vmo::pin`]), maps them into
+//! the device's IO address space and returns the addresses the device
+//! must use. Until the pin is released, the BTI holds a reference to the
+//! VMO and the pages stay where they are.
+//!
+//! # Quarantine
+//!
+//! A pin whose owner goes away without unpinning may still be the target
+//! of DMA the device was told about. Such pins are quarantined instead
+//! of released: the mappings stay in place until the driver, after
+//! resetting the device, calls [`Bti::release_quarantine`], or the BTI is
+//! destroyed.
+
+use crate::kernel::dev::iommu::Iommu;
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::vmo::Vmo;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::{PAddr, Result};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(BTI_PINS, "kernel.bti.pins");
+KCOUNTER!(BTI_QUARANTINED, "kernel.bti.quarantined");
+
+/// BTI object identifier
+pub type BtiId = u64;
+
+/// Identifies a pin within its BTI
+pub type PinId = u64;
+
+/// Next BTI ID counter
+static NEXT_BTI_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Page size of pins
+const PIN_PAGE_SIZE: u64 = 4096;
+
+/// A pinned VMO range
+pub struct Pin {
+    vmo: Arc<Vmo>,
+    offset: u64,
+    size: u64,
+    addrs: Vec<u64>,
+}
+
+impl Pin {
+    /// Pinned VMO
+    pub fn vmo(&self) -> &Arc<Vmo> {
+        &self.vmo
+    }
+
+    /// Offset and size of the range in the VMO
+    pub fn range(&self) -> (u64, u64) {
+        (self.offset, self.size)
+    }
+
+    /// Device address of each page
+    pub fn addrs(&self) -> &[u64] {
+        &self.addrs
+    }
+}
+
+/// Whether device addresses are one contiguous run of pages
+pub fn is_contiguous(addrs: &[u64]) -> bool {
+    addrs.windows(2).all(|pair| pair[1] == pair[0] + PIN_PAGE_SIZE)
+}
+
+/// A bus transaction initiator
+pub struct Bti {
+    /// Object ID
+    pub id: BtiId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// Device the BTI stands for
+    pub bus_txn_id: u64,
+
+    iommu: Arc<dyn Iommu>,
+    pins: SpinMutex<BTreeMap<PinId, Pin>>,
+    quarantine: SpinMutex<Vec<Pin>>,
+    next_pin: AtomicU64,
+}
+
+impl Bti {
+    /// Pin `size` bytes of `vmo` at `offset` for the device
+    ///
+    /// `offset` and `size` must be page-aligned. Returns the pin and the
+    /// device address of each page.
+    pub fn pin(&self, vmo: Arc<Vmo>, offset: u64, size: u64, perms: u32) -> Result<(PinId, Vec<u64>)> {
+        if size == 0 || offset % PIN_PAGE_SIZE != 0 || size % PIN_PAGE_SIZE != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if offset.checked_add(size).map_or(true, |end| end > vmo.size() as u64) {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        vmo.pin(offset as usize, size as usize)?;
+
+        let first = (offset / PIN_PAGE_SIZE) as usize;
+        let count = (size / PIN_PAGE_SIZE) as usize;
+        let mapped = (first..first + count)
+            .map(|page| vmo.commit_page_paddr(page))
+            .collect::<Result<Vec<PAddr>>>()
+            .and_then(|pages| self.iommu.map(self.bus_txn_id, &pages, perms));
+        let addrs = match mapped {
+            Ok(addrs) => addrs,
+            Err(err) => {
+                let _ = vmo.unpin(offset as usize, size as usize);
+                return Err(err);
+            }
+        };
+
+        let id = self.next_pin.fetch_add(1, Ordering::Relaxed);
+        self.pins.lock().insert(id, Pin { vmo, offset, size, addrs: addrs.clone() });
+        BTI_PINS.add(1);
+        Ok((id, addrs))
+    }
+
+    /// Unmap a pin, unpin its pages and drop its VMO reference
+    ///
+    /// The device must no longer access the pages.
+    pub fn unpin(&self, pin: PinId) -> Result {
+        let pin = self.pins.lock().remove(&pin).ok_or(RX_ERR_NOT_FOUND)?;
+        self.release(pin)
+    }
+
+    /// Keep a pin's mappings until the quarantine is released
+    pub fn quarantine(&self, pin: PinId) -> Result {
+        let pin = self.pins.lock().remove(&pin).ok_or(RX_ERR_NOT_FOUND)?;
+        self.quarantine.lock().push(pin);
+        BTI_QUARANTINED.add(1);
+        Ok(())
+    }
+
+    /// Release every quarantined pin, returning how many there were
+    pub fn release_quarantine(&self) -> usize {
+        let pins = core::mem::take(&mut *self.quarantine.lock());
+        let count = pins.len();
+        for pin in pins {
+            let _ = self.release(pin);
+        }
+        count
+    }
+
+    /// Number of live pins
+    pub fn pin_count(&self) -> usize {
+        self.pins.lock().len()
+    }
+
+    /// Number of quarantined pins
+    pub fn quarantine_count(&self) -> usize {
+        self.quarantine.lock().len()
+    }
+
+    fn release(&self, pin: Pin) -> Result {
+        let unmapped = match pin.addrs.first() {
+            Some(&base) => self.iommu.unmap(self.bus_txn_id, base, pin.addrs.len()),
+            None => Ok(()),
+        };
+        pin.vmo.unpin(pin.offset as usize, pin.size as usize)?;
+        unmapped
+    }
+}
+
+/// ============================================================================
+/// Registry
+/// ============================================================================
+
+/// Live BTIs by ID
+static BTIS: SpinMutex<BTreeMap<BtiId, Arc<Bti>>> = SpinMutex::new(BTreeMap::new());
+
+/// Create and register a BTI for `bus_txn_id` on `iommu`
+pub fn create(iommu: Arc<dyn Iommu>, bus_txn_id: u64) -> Result<BtiId> {
+    if !iommu.is_valid_bus_txn_id(bus_txn_id) {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    let id = NEXT_BTI_ID.fetch_add(1, Ordering::Relaxed);
+    let bti = Bti {
+        id,
+        koid: alloc_koid(),
+        bus_txn_id,
+        iommu,
+        pins: SpinMutex::new(BTreeMap::new()),
+        quarantine: SpinMutex::new(Vec::new()),
+        next_pin: AtomicU64::new(1),
+    };
+    BTIS.lock().insert(id, Arc::new(bti));
+    Ok(id)
+}
+
+/// Look up a BTI
+pub fn get(id: BtiId) -> Result<Arc<Bti>> {
+    BTIS.lock().get(&id).cloned().ok_or(RX_ERR_NOT_FOUND)
+}
+
+/// Destroy a BTI, releasing its pins and quarantine and the device's IO
+/// address space
+pub fn destroy(id: BtiId) -> Result {
+    let bti = BTIS.lock().remove(&id).ok_or(RX_ERR_NOT_FOUND)?;
+    let pins = core::mem::take(&mut *bti.pins.lock());
+    for (_, pin) in pins {
+        let _ = bti.release(pin);
+    }
+    bti.release_quarantine();
+    bti.iommu.clear_mappings(bti.bus_txn_id);
+    Ok(())
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::iommu::{perm, DummyIommu};
+
+    #[test]
+    fn test_is_contiguous() {
+        assert!(is_contiguous(&[0x1000, 0x2000, 0x3000]));
+        assert!(is_contiguous(&[0x5000]));
+        assert!(!is_contiguous(&[0x1000, 0x3000]));
+    }
+
+    #[test]
+    fn test_pin_quarantine() {
+        let id = create(Arc::new(DummyIommu::new()), 0x0010).unwrap();
+        let bti = get(id).unwrap();
+        let vmo = Arc::new(Vmo::create_physical(0x1000_0000, 0x2000).unwrap());
+
+        assert_eq!(bti.pin(vmo.clone(), 0x800, 0x1000, perm::READ).unwrap_err(), RX_ERR_INVALID_ARGS);
+        assert_eq!(bti.pin(vmo.clone(), 0x1000, 0x2000, perm::READ).unwrap_err(), RX_ERR_OUT_OF_RANGE);
+
+        let (pin, addrs) = bti.pin(vmo.clone(), 0, 0x2000, perm::READ).unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert!(is_contiguous(&addrs));
+        assert_eq!(vmo.pinned_count(), 2);
+
+        // Quarantined pages stay pinned
+        bti.quarantine(pin).unwrap();
+        assert_eq!(bti.pin_count(), 0);
+        assert_eq!(bti.quarantine_count(), 1);
+        assert_eq!(bti.unpin(pin), Err(RX_ERR_NOT_FOUND));
+        assert_eq!(vmo.pinned_count(), 2);
+        assert_eq!(bti.release_quarantine(), 1);
+        assert_eq!(vmo.pinned_count(), 0);
+
+        destroy(id).unwrap();
+        assert!(get(id).is_err());
+    }
+}
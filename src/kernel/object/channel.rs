@@ -17,6 +17,40 @@
 //! - **Handle passing**: Handles can be transferred with rights reduction
 //! - **Peer closure**: One end closed → PEER_CLOSED signal to other
 //!
+//! # Limits
+//!
+//! A message carries at most `MAX_MSG_SIZE` bytes and `MAX_MSG_HANDLES`
+//! handles; more fails with `RX_ERR_OUT_OF_RANGE`. A read whose buffers
+//! are too small fails with `RX_ERR_BUFFER_TOO_SMALL` and leaves the
+//! message queued, unless the reader asked to discard it.
+//!
+//! # Quotas
+//!
+//! Each endpoint queues at most `max_queue_bytes` bytes and
+//! `max_queue_msgs` messages. A write past either quota fails with
+//! `RX_ERR_SHOULD_WAIT`; the endpoint's write event is unsignaled while
+//! the queue is full and signaled again once a read frees space. Queued
+//! messages are also charged to the system's
+//! [`message_packet`](super::message_packet) pool, and a write that the
+//! pool, the writer's process or its job can't afford fails with
+//! `RX_ERR_NO_MEMORY`.
+//!
+//! # Calls
+//!
+//! A call message starts with a 4-byte transaction ID and records the
+//! calling thread and its priority, so whoever reads it can be lent that
+//! priority until it replies (see `rx_channel_call_etc`). The reply is
+//! the first message back whose transaction ID matches; [`Channel::take_reply`]
+//! takes it out of the queue ahead of other messages. A caller waiting for
+//! its reply blocks in [`Channel::block_for_reply`] and is woken when the
+//! reply is queued or the peer closes.
+//!
+//! # Message Buffers
+//!
+//! Message data up to `MSG_BUFFER_SMALL` bytes lives in a buffer from the
+//! `channel.msg.256` object cache, up to `MSG_BUFFER_LARGE` bytes in one
+//! from `channel.msg.4k`, and anything larger on the heap.
+//!
 //! # Usage
 //!
 //! ```rust
@@ -26,15 +60,28 @@
 //! ```
 
 
-use crate::kernel::object::handle::{Handle, HandleId, Rights};
+use crate::kernel::lib::slab::ObjectCache;
+use crate::kernel::object::handle::{Handle, HandleId, ObjectType, Rights};
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::leak::Tracked;
+use crate::kernel::object::message_packet::{self, PacketCharge};
 use crate::kernel::sync::event::{Event, EventFlags};
-use crate::kernel::sync::Mutex;
+use crate::kernel::sync::{Mutex, MutexGuard};
+use crate::kernel::sched;
+use crate::kernel::thread::{self, BlockReason, ThreadId, ThreadPriority, TID_INVALID};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
+use alloc::alloc::{AllocError, Allocator, Layout};
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
+use core::ptr::NonNull;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(CHANNEL_QUOTA_FULL, "channel.quota.should_wait");
+
 /// ============================================================================
 /// Channel ID
 /// ============================================================================
@@ -60,19 +107,105 @@ pub const MAX_MSG_SIZE: usize = 64 * 1024;
 /// Maximum handles per message
 pub const MAX_MSG_HANDLES: usize = 64;
 
+/// Default quota of queued bytes per endpoint
+pub const DEFAULT_MAX_QUEUE_BYTES: usize = 256 * 1024;
+
+/// Default quota of queued messages per endpoint
+pub const DEFAULT_MAX_QUEUE_MSGS: usize = 1024;
+
+/// Largest message data kept in a small buffer
+pub const MSG_BUFFER_SMALL: usize = 256;
+
+/// Largest message data kept in a large buffer
+pub const MSG_BUFFER_LARGE: usize = 4096;
+
+static MSG_BUFFER_SMALL_CACHE: ObjectCache =
+    ObjectCache::new("channel.msg.256", MSG_BUFFER_SMALL, 8);
+static MSG_BUFFER_LARGE_CACHE: ObjectCache =
+    ObjectCache::new("channel.msg.4k", MSG_BUFFER_LARGE, 8);
+
+/// Allocator for message data, picking a buffer cache by size
+///
+/// Data too big for the large buffers falls through to the heap.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageBufferAlloc;
+
+impl MessageBufferAlloc {
+    /// Cache for buffers of `layout`
+    fn cache(layout: Layout) -> &'static ObjectCache {
+        if layout.size() <= MSG_BUFFER_SMALL {
+            &MSG_BUFFER_SMALL_CACHE
+        } else {
+            &MSG_BUFFER_LARGE_CACHE
+        }
+    }
+}
+
+unsafe impl Allocator for MessageBufferAlloc {
+    fn allocate(&self, layout: Layout) -> core::result::Result<NonNull<[u8]>, AllocError> {
+        Self::cache(layout).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        Self::cache(layout).deallocate(ptr, layout)
+    }
+}
+
+/// Bytes at the start of a call message holding its transaction ID
+pub const TXID_SIZE: usize = 4;
+
+/// The caller waiting on a call message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallInfo {
+    /// Transaction ID the reply must carry
+    pub txid: u32,
+
+    /// Thread waiting for the reply
+    pub caller: ThreadId,
+
+    /// Priority to lend the thread that takes the message
+    pub priority: ThreadPriority,
+}
+
 /// Message data
 pub struct Message {
     /// Message bytes
-    pub data: Vec<u8>,
+    pub data: Vec<u8, MessageBufferAlloc>,
 
     /// Handles being transferred
     pub handles: Vec<Handle>,
+
+    /// Packet pool charge, held while the message is queued
+    pub charge: Option<PacketCharge>,
+
+    /// Set if a caller is blocked waiting for the reply
+    pub call: Option<CallInfo>,
 }
 
 impl Message {
-    /// Create a new message
-    pub fn new(data: Vec<u8>, handles: Vec<Handle>) -> Self {
-        Self { data, handles }
+    /// Create a new message, copying `data` into a message buffer
+    pub fn new(data: &[u8], handles: Vec<Handle>) -> Self {
+        let mut buf = Vec::with_capacity_in(data.len(), MessageBufferAlloc);
+        buf.extend_from_slice(data);
+        Self { data: buf, handles, charge: None, call: None }
+    }
+
+    /// Transaction ID in the first `TXID_SIZE` bytes, if the message is
+    /// long enough to have one
+    pub fn txid(&self) -> Option<u32> {
+        let bytes = self.data.get(..TXID_SIZE)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Bytes a message of `data_size` bytes and `handle_count` handles
+    /// counts against queue quotas
+    pub fn footprint_of(data_size: usize, handle_count: usize) -> usize {
+        data_size + handle_count * core::mem::size_of::<Handle>()
+    }
+
+    /// Bytes this message counts against queue quotas
+    pub fn footprint(&self) -> usize {
+        Self::footprint_of(self.data_size(), self.handle_count())
     }
 
     /// Get message data size
@@ -136,15 +269,24 @@ pub struct Channel {
     /// Channel ID
     pub id: ChannelId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Peer channel ID
     pub peer: Mutex<Option<ChannelId>>,
 
+    /// Koid of the peer endpoint
+    pub peer_koid: Koid,
+
     /// Message queue
     pub queue: Mutex<VecDeque<Message>>,
 
     /// Maximum queue depth (in bytes)
     pub max_queue_bytes: usize,
 
+    /// Maximum queue depth (in messages)
+    pub max_queue_msgs: usize,
+
     /// Current queue size (in bytes)
     pub queue_size: AtomicUsize,
 
@@ -160,8 +302,17 @@ pub struct Channel {
     /// Number of waiters
     pub waiter_count: AtomicUsize,
 
+    /// Thread that last took a message, `TID_INVALID` if none
+    pub last_reader: AtomicU64,
+
+    /// Callers blocked for a reply, as (transaction ID, thread) pairs
+    reply_waiters: Mutex<Vec<(u32, ThreadId)>>,
+
     /// Reference count
     pub ref_count: AtomicUsize,
+
+    /// Entry in the live object table (debug builds)
+    tracked: Tracked,
 }
 
 impl Channel {
@@ -173,31 +324,45 @@ impl Channel {
     pub fn create() -> Result<(Self, Self)> {
         let id_a = alloc_channel_id();
         let id_b = alloc_channel_id();
+        let koid_a = alloc_koid();
+        let koid_b = alloc_koid();
 
         let channel_a = Self {
             id: id_a,
+            koid: koid_a,
             peer: Mutex::new(Some(id_b)),
+            peer_koid: koid_b,
             queue: Mutex::new(VecDeque::new()),
-            max_queue_bytes: 256 * 1024, // 256KB default
+            max_queue_bytes: DEFAULT_MAX_QUEUE_BYTES,
+            max_queue_msgs: DEFAULT_MAX_QUEUE_MSGS,
             queue_size: AtomicUsize::new(0),
             read_event: Event::new(false, EventFlags::empty()),
             write_event: Event::new(true, EventFlags::empty()), // Initially writable
             state: Mutex::new(ChannelState::Active),
             waiter_count: AtomicUsize::new(0),
+            last_reader: AtomicU64::new(TID_INVALID),
+            reply_waiters: Mutex::new(Vec::new()),
             ref_count: AtomicUsize::new(1),
+            tracked: Tracked::new(koid_a, ObjectType::Channel),
         };
 
         let channel_b = Self {
             id: id_b,
+            koid: koid_b,
             peer: Mutex::new(Some(id_a)),
+            peer_koid: koid_a,
             queue: Mutex::new(VecDeque::new()),
-            max_queue_bytes: 256 * 1024,
+            max_queue_bytes: DEFAULT_MAX_QUEUE_BYTES,
+            max_queue_msgs: DEFAULT_MAX_QUEUE_MSGS,
             queue_size: AtomicUsize::new(0),
             read_event: Event::new(false, EventFlags::empty()),
             write_event: Event::new(true, EventFlags::empty()),
             state: Mutex::new(ChannelState::Active),
             waiter_count: AtomicUsize::new(0),
+            last_reader: AtomicU64::new(TID_INVALID),
+            reply_waiters: Mutex::new(Vec::new()),
             ref_count: AtomicUsize::new(1),
+            tracked: Tracked::new(koid_b, ObjectType::Channel),
         };
 
         Ok((channel_a, channel_b))
@@ -213,7 +378,30 @@ impl Channel {
     /// # Returns
     ///
     /// Number of bytes written
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_SHOULD_WAIT` - the queue's byte or message quota is used up
+    /// - `RX_ERR_NO_MEMORY` - the packet pool, or the writer's process or
+    ///   job, is out of budget
     pub fn write(&self, data: &[u8], handles: Vec<Handle>) -> Result<usize> {
+        self.enqueue(data, handles, None)
+    }
+
+    /// Write a call message for `call.caller`
+    ///
+    /// `data` must start with the transaction ID; the errors are those of
+    /// [`write`](Self::write), or `RX_ERR_INVALID_ARGS` if `data` is too
+    /// short to hold it.
+    pub fn write_call(&self, data: &[u8], handles: Vec<Handle>, call: CallInfo) -> Result<usize> {
+        if data.len() < TXID_SIZE {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        self.enqueue(data, handles, Some(call))
+    }
+
+    /// Queue a message, checking limits and quotas
+    fn enqueue(&self, data: &[u8], handles: Vec<Handle>, call: Option<CallInfo>) -> Result<usize> {
         // Check state
         let state = *self.state.lock();
         if state == ChannelState::Closed {
@@ -222,12 +410,12 @@ impl Channel {
 
         // Validate data size
         if data.len() > MAX_MSG_SIZE {
-            return Err(RX_ERR_INVALID_ARGS);
+            return Err(RX_ERR_OUT_OF_RANGE);
         }
 
         // Validate handle count
         if handles.len() > MAX_MSG_HANDLES {
-            return Err(RX_ERR_INVALID_ARGS);
+            return Err(RX_ERR_OUT_OF_RANGE);
         }
 
         // Validate handles have TRANSFER right
@@ -235,14 +423,8 @@ impl Channel {
             h.require(Rights::TRANSFER)?;
         }
 
-        // Check queue capacity
         let data_size = data.len();
-        let handles_size = handles.len() * core::mem::size_of::<Handle>();
-        let total_size = data_size + handles_size;
-
-        if self.queue_size.load(Ordering::Acquire) + total_size > self.max_queue_bytes {
-            return Err(RX_ERR_SHOULD_WAIT);
-        }
+        let total_size = Message::footprint_of(data_size, handles.len());
 
         // Check peer
         let peer_id = {
@@ -254,20 +436,38 @@ impl Channel {
             return Err(RX_ERR_PEER_CLOSED);
         }
 
-        // Create message
-        let msg = Message::new(data.to_vec(), handles);
+        // Check quotas under the queue lock, so racing writers can't both fit
+        let mut queue = self.queue.lock();
+        let queued = self.queue_size.load(Ordering::Acquire);
+        if self.is_full(queue.len(), queued) || queued + total_size > self.max_queue_bytes {
+            CHANNEL_QUOTA_FULL.add(1);
+            return Err(RX_ERR_SHOULD_WAIT);
+        }
+
+        // Create message, charged until it is read or discarded
+        let mut msg = Message::new(data, handles);
+        msg.charge = Some(message_packet::charge(total_size)?);
+        msg.call = call;
+        let reply_to = if call.is_none() { msg.txid() } else { None };
 
         // Add to queue
-        {
-            let mut queue = self.queue.lock();
-            queue.push_back(msg);
+        queue.push_back(msg);
+        let queued = self.queue_size.fetch_add(total_size, Ordering::Release) + total_size;
+        let full = self.is_full(queue.len(), queued);
+        drop(queue);
+
+        // Writers wait for a reader to make room
+        if full {
+            self.write_event.unsignal();
         }
 
-        self.queue_size.fetch_add(total_size, Ordering::Release);
-
         // Signal read event
         self.read_event.signal();
 
+        if let Some(txid) = reply_to {
+            self.wake_reply_waiters(|waiting| waiting == txid);
+        }
+
         Ok(data_size)
     }
 
@@ -282,50 +482,160 @@ impl Channel {
     ///
     /// Tuple of (bytes_read, handles_received)
     pub fn read(&self, buf: &mut [u8], handle_buf: &mut Vec<Handle>) -> Result<(usize, usize)> {
+        let msg = self.take_message(buf.len(), handle_buf.capacity(), false)?;
+        let msg_data_size = msg.data_size();
+        let msg_handle_count = msg.handle_count();
+
+        // Copy data
+        buf[..msg_data_size].copy_from_slice(&msg.data);
+
+        // Transfer handles
+        *handle_buf = msg.handles;
+
+        Ok((msg_data_size, msg_handle_count))
+    }
+
+    /// Take the next message if it fits in `data_capacity` bytes and
+    /// `handle_capacity` handles
+    ///
+    /// A message that doesn't fit stays queued, or with `may_discard` is
+    /// dropped and its handles closed; either way `RX_ERR_BUFFER_TOO_SMALL`
+    /// is returned.
+    pub fn take_message(&self, data_capacity: usize, handle_capacity: usize, may_discard: bool) -> Result<Message> {
         // Check state
         let state = *self.state.lock();
         if state == ChannelState::Closed {
             return Err(RX_ERR_BAD_STATE);
         }
 
-        // Get message from queue
-        let msg = {
-            let mut queue = self.queue.lock();
-            queue.pop_front().ok_or(RX_ERR_SHOULD_WAIT)?
-        };
-
-        // Calculate sizes
-        let msg_data_size = msg.data_size();
-        let msg_handle_count = msg.handle_count();
-
-        // Validate buffer sizes
-        if buf.len() < msg_data_size {
+        let queue = self.queue.lock();
+        let front = queue.front().ok_or(RX_ERR_SHOULD_WAIT)?;
+        let fits = front.data_size() <= data_capacity && front.handle_count() <= handle_capacity;
+        if !fits && !may_discard {
             return Err(RX_ERR_BUFFER_TOO_SMALL);
         }
+        self.take_at(queue, 0, fits)
+    }
 
-        if handle_buf.capacity() < msg_handle_count {
-            return Err(RX_ERR_BUFFER_TOO_SMALL);
+    /// Take the reply to transaction `txid`, ahead of any other messages
+    ///
+    /// A reply that doesn't fit in `data_capacity` bytes and
+    /// `handle_capacity` handles is dropped and its handles closed, and
+    /// `RX_ERR_BUFFER_TOO_SMALL` returned. Returns `RX_ERR_SHOULD_WAIT` if
+    /// the reply hasn't arrived.
+    pub fn take_reply(&self, txid: u32, data_capacity: usize, handle_capacity: usize) -> Result<Message> {
+        let queue = self.queue.lock();
+        let pos = queue
+            .iter()
+            .position(|msg| msg.call.is_none() && msg.txid() == Some(txid))
+            .ok_or(RX_ERR_SHOULD_WAIT)?;
+        let fits = queue[pos].data_size() <= data_capacity && queue[pos].handle_count() <= handle_capacity;
+        self.take_at(queue, pos, fits)
+    }
+
+    /// Block the current thread until the reply to `txid` is queued
+    ///
+    /// Returns at once if the reply is already queued or the peer has
+    /// closed. Otherwise the thread leaves the run queue until a reply
+    /// with `txid` is written, the peer closes, or something else wakes
+    /// it, such as a deadline timer; callers check again on return.
+    pub fn block_for_reply(&self, txid: u32) {
+        let Some(current) = thread::get_current_thread() else {
+            return;
+        };
+        let tid = current.tid();
+
+        // Checked under the waiter lock, so a reply queued after the check
+        // finds the thread blocked and wakes it
+        {
+            let mut waiters = self.reply_waiters.lock();
+            if self.has_reply(txid) || !self.is_peer_alive() {
+                return;
+            }
+            waiters.push((txid, tid));
+            sched::block_current(BlockReason::ChannelRead);
         }
+        thread::reschedule();
 
-        // Update queue size
-        let handles_size = msg_handle_count * core::mem::size_of::<Handle>();
-        self.queue_size.fetch_sub(msg_data_size + handles_size, Ordering::Release);
+        // Woken by something other than the reply
+        self.reply_waiters.lock().retain(|&(_, waiter)| waiter != tid);
+    }
 
-        // Copy data
-        buf[..msg_data_size].copy_from_slice(&msg.data);
+    /// Whether the reply to `txid` is queued
+    fn has_reply(&self, txid: u32) -> bool {
+        self.queue.lock().iter().any(|msg| msg.call.is_none() && msg.txid() == Some(txid))
+    }
 
-        // Transfer handles
-        *handle_buf = msg.handles;
+    /// Wake the callers waiting for a reply whose transaction ID matches
+    fn wake_reply_waiters(&self, matches: impl Fn(u32) -> bool) {
+        let mut woken = Vec::new();
+        self.reply_waiters.lock().retain(|&(txid, tid)| {
+            if matches(txid) {
+                woken.push(tid);
+            }
+            !matches(txid)
+        });
+        for tid in woken {
+            sched::wake(tid);
+        }
+    }
+
+    /// Remove the message at `pos`, discarding it unless it `fits`
+    fn take_at(&self, mut queue: MutexGuard<'_, VecDeque<Message>>, pos: usize, fits: bool) -> Result<Message> {
+        let mut msg = queue.remove(pos).ok_or(RX_ERR_SHOULD_WAIT)?;
+        let now_empty = queue.is_empty();
+        let footprint = msg.footprint();
+        let queued = self.queue_size.fetch_sub(footprint, Ordering::Release) - footprint;
+        let full = self.is_full(queue.len(), queued);
+        drop(queue);
+
+        // The message no longer counts against the pool
+        msg.charge = None;
 
         // If queue is now empty, unsignal read event
-        if self.queue.lock().is_empty() {
+        if now_empty {
             self.read_event.unsignal();
         }
 
-        // Signal write event (space available)
-        self.write_event.signal();
+        // Writable again once below quota
+        if !full {
+            self.write_event.signal();
+        }
 
-        Ok((msg_data_size, msg_handle_count))
+        if !fits {
+            for handle in &msg.handles {
+                handle.close();
+            }
+            return Err(RX_ERR_BUFFER_TOO_SMALL);
+        }
+        Ok(msg)
+    }
+
+    /// Remember the thread that took a message, for callers to yield to
+    pub fn note_reader(&self, tid: ThreadId) {
+        self.last_reader.store(tid, Ordering::Relaxed);
+    }
+
+    /// The thread that last took a message, if any
+    pub fn last_reader(&self) -> Option<ThreadId> {
+        match self.last_reader.load(Ordering::Relaxed) {
+            TID_INVALID => None,
+            tid => Some(tid),
+        }
+    }
+
+    /// Whether a queue of `count` messages and `bytes` bytes is at quota
+    fn is_full(&self, count: usize, bytes: usize) -> bool {
+        count >= self.max_queue_msgs || bytes >= self.max_queue_bytes
+    }
+
+    /// Whether the queue is below its quotas
+    ///
+    /// A writable endpoint may still refuse a message too large for the
+    /// bytes left.
+    pub fn is_writable(&self) -> bool {
+        let queue = self.queue.lock();
+        !self.is_full(queue.len(), self.queue_size.load(Ordering::Acquire))
     }
 
     /// Get message count in queue
@@ -372,6 +682,9 @@ impl Channel {
 
         // Signal read event so reader can detect peer closure
         self.read_event.signal();
+
+        // No reply is coming
+        self.wake_reply_waiters(|_| true);
     }
 
     /// Increment reference count
@@ -429,10 +742,109 @@ mod tests {
     #[test]
     fn test_message() {
         let data = vec![1, 2, 3, 4];
-        let msg = Message::new(data.clone(), vec![]);
+        let msg = Message::new(&data, vec![]);
 
         assert_eq!(msg.data_size(), 4);
         assert_eq!(msg.handle_count(), 0);
         assert!(!msg.is_empty());
     }
+
+    #[test]
+    fn test_channel_limits() {
+        let (ch, _peer) = Channel::create().unwrap();
+
+        let big = vec![0u8; MAX_MSG_SIZE + 1];
+        assert_eq!(ch.write(&big, vec![]), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(ch.write(&big[..MAX_MSG_SIZE], vec![]), Ok(MAX_MSG_SIZE));
+
+        // Too small a buffer leaves the message queued...
+        assert_eq!(ch.take_message(16, 0, false).err(), Some(RX_ERR_BUFFER_TOO_SMALL));
+        assert_eq!(ch.msg_count(), 1);
+
+        // ...unless the reader discards it
+        assert_eq!(ch.take_message(16, 0, true).err(), Some(RX_ERR_BUFFER_TOO_SMALL));
+        assert_eq!(ch.msg_count(), 0);
+        assert_eq!(ch.queue_size(), 0);
+        assert_eq!(ch.take_message(16, 0, false).err(), Some(RX_ERR_SHOULD_WAIT));
+    }
+
+    #[test]
+    fn test_channel_take_reply() {
+        let (ch, _peer) = Channel::create().unwrap();
+        let call = CallInfo { txid: 7, caller: 1, priority: 200 };
+
+        assert_eq!(ch.write_call(&[1, 2], vec![], call), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(ch.write_call(&7u32.to_le_bytes(), vec![], call), Ok(TXID_SIZE));
+        ch.write(&[9, 0, 0, 0, 1], vec![]).unwrap();
+        ch.write(&[7, 0, 0, 0, 2], vec![]).unwrap();
+
+        // The reply skips the call message and the other transaction
+        assert_eq!(ch.take_reply(8, 16, 0).err(), Some(RX_ERR_SHOULD_WAIT));
+        let reply = ch.take_reply(7, 16, 0).unwrap();
+        assert_eq!(reply.data[..], [7, 0, 0, 0, 2]);
+        assert_eq!(ch.take_reply(7, 16, 0).err(), Some(RX_ERR_SHOULD_WAIT));
+
+        // Normal reads see the rest in order
+        assert_eq!(ch.take_message(16, 0, false).unwrap().call, Some(call));
+        assert_eq!(ch.take_message(16, 0, false).unwrap().txid(), Some(9));
+    }
+
+    #[test]
+    fn test_channel_reply_waiters() {
+        let (ch, _peer) = Channel::create().unwrap();
+        ch.reply_waiters.lock().extend([(7, 100), (8, 101), (7, 102)]);
+
+        // Call messages and other transactions wake nobody
+        let call = CallInfo { txid: 7, caller: 1, priority: 200 };
+        ch.write_call(&7u32.to_le_bytes(), vec![], call).unwrap();
+        ch.write(&[9, 0, 0, 0], vec![]).unwrap();
+        assert_eq!(ch.reply_waiters.lock().len(), 3);
+
+        // A reply wakes every caller waiting on its transaction
+        ch.write(&[7, 0, 0, 0], vec![]).unwrap();
+        assert!(ch.has_reply(7));
+        assert!(!ch.has_reply(8));
+        assert_eq!(*ch.reply_waiters.lock(), vec![(8, 101)]);
+
+        // With the peer gone no reply is coming
+        ch.on_peer_closed();
+        assert!(ch.reply_waiters.lock().is_empty());
+    }
+
+    #[test]
+    fn test_channel_message_quota() {
+        let (mut ch, _peer) = Channel::create().unwrap();
+        ch.max_queue_msgs = 2;
+
+        assert_eq!(ch.write(&[1], vec![]), Ok(1));
+        assert!(ch.is_writable());
+        assert_eq!(ch.write(&[2], vec![]), Ok(1));
+        assert!(!ch.is_writable());
+        assert!(!ch.write_event.is_signaled());
+        assert_eq!(ch.write(&[3], vec![]), Err(RX_ERR_SHOULD_WAIT));
+
+        // A read makes the endpoint writable again
+        assert_eq!(ch.take_message(16, 0, false).unwrap().data[..], [1]);
+        assert!(ch.is_writable());
+        assert!(ch.write_event.is_signaled());
+        assert_eq!(ch.write(&[3], vec![]), Ok(1));
+    }
+
+    #[test]
+    fn test_channel_byte_quota() {
+        let (mut ch, _peer) = Channel::create().unwrap();
+        ch.max_queue_bytes = 100;
+
+        assert_eq!(ch.write(&[0; 60], vec![]), Ok(60));
+        assert_eq!(ch.write(&[0; 60], vec![]), Err(RX_ERR_SHOULD_WAIT));
+        assert_eq!(ch.write(&[0; 40], vec![]), Ok(40));
+        assert!(!ch.is_writable());
+
+        // Discarding a message frees its space too
+        assert!(ch.take_message(16, 0, true).is_err());
+        assert!(ch.is_writable());
+        let msg = ch.take_message(64, 0, false).unwrap();
+        assert!(msg.charge.is_none());
+        assert_eq!(ch.queue_size(), 0);
+    }
 }
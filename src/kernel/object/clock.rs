@@ -0,0 +1,601 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Clock Objects
+//!
+//! A clock is a synthetic timeline derived from the monotonic clock (the
+//! reference) by an affine transform:
+//!
+//! ```text
+//! synthetic = synthetic_offset
+//!     + (reference - reference_offset) * synthetic_ticks / reference_ticks
+//! ```
+//!
+//! The kernel never changes a clock on its own. Whoever holds it, such as
+//! a time daemon for the UTC clock, disciplines it with [`Clock::update`]:
+//!
+//! - **Value**: step the clock to a new time. A `MONOTONIC` clock can't be
+//!   stepped backwards and a `CONTINUOUS` one can't be stepped at all once
+//!   started.
+//! - **Rate**: run the clock up to [`MAX_RATE_ADJUST_PPM`] fast or slow to
+//!   correct for reference oscillator drift.
+//! - **Slew**: absorb an offset gradually, by running [`SLEW_RATE_PPM`]
+//!   faster or slower than the current rate until it is made up. The
+//!   clock stays continuous, so this is how a `CONTINUOUS` clock is
+//!   corrected.
+//!
+//! A new rate or value ends any slew in progress.
+//!
+//! # Starting
+//!
+//! A clock reads its backstop time until the first value update starts it,
+//! or from creation with `AUTO_START`, which starts it equal to the
+//! reference. It never reads less than the backstop.
+//!
+//! # Details
+//!
+//! [`Clock::details`] reports the transform in effect, the one that takes
+//! over when a slew ends, and the update history, so userspace can convert
+//! between monotonic and synthetic time without a syscall per reading.
+//! The system UTC clock's details are also published in the
+//! [`timepage`](crate::kernel::lib::timepage) on every update.
+//!
+//! Clock state is behind a [`SeqLock`], so reading a clock never waits on
+//! other readers.
+
+use crate::kernel::lib::timepage;
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::sync::seqlock::SeqLock;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Clock object identifier
+pub type ClockId = u64;
+
+/// Next clock ID counter
+static NEXT_CLOCK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Clock creation options
+pub mod clock_opt {
+    /// Never goes backwards
+    pub const MONOTONIC: u64 = 1 << 0;
+
+    /// Never jumps; implies `MONOTONIC`
+    pub const CONTINUOUS: u64 = 1 << 1;
+
+    /// Start equal to the reference clock
+    pub const AUTO_START: u64 = 1 << 2;
+
+    /// Every valid option
+    pub const ALL: u64 = MONOTONIC | CONTINUOUS | AUTO_START;
+}
+
+/// Which fields of a [`ClockUpdate`] apply
+pub mod update_flags {
+    /// Step to `value`
+    pub const VALUE_VALID: u32 = 1 << 0;
+
+    /// Set the rate to `rate_adjust_ppm`
+    pub const RATE_ADJUST_VALID: u32 = 1 << 1;
+
+    /// Set the error bound to `error_bound`
+    pub const ERROR_BOUND_VALID: u32 = 1 << 2;
+
+    /// Slew by `slew_offset`
+    pub const SLEW_VALID: u32 = 1 << 3;
+
+    /// Every valid flag
+    pub const ALL: u32 = VALUE_VALID | RATE_ADJUST_VALID | ERROR_BOUND_VALID | SLEW_VALID;
+}
+
+/// Largest rate adjustment, in parts per million
+pub const MAX_RATE_ADJUST_PPM: i32 = 1000;
+
+/// Rate change applied while slewing, in parts per million
+pub const SLEW_RATE_PPM: i64 = 500;
+
+/// Largest offset a single slew absorbs (one minute, which takes about
+/// 33 hours); larger corrections have to step the clock
+pub const MAX_SLEW_NS: i64 = 60_000_000_000;
+
+/// Error bound of a clock nobody has estimated
+pub const ERROR_BOUND_UNKNOWN: u64 = u64::MAX;
+
+const PPM: i64 = 1_000_000;
+
+/// Affine map from reference time to synthetic time
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transform {
+    /// Reference time the transform is anchored at
+    pub reference_offset: i64,
+
+    /// Synthetic time at `reference_offset`
+    pub synthetic_offset: i64,
+
+    /// Synthetic nanoseconds per `reference_ticks` reference nanoseconds
+    pub synthetic_ticks: u32,
+
+    /// Reference nanoseconds per `synthetic_ticks`
+    pub reference_ticks: u32,
+}
+
+impl Transform {
+    /// A stopped clock reading `value`
+    pub const fn stopped(value: i64) -> Self {
+        Self { reference_offset: 0, synthetic_offset: value, synthetic_ticks: 0, reference_ticks: 1 }
+    }
+
+    /// A clock reading `synthetic` at `reference`, running `ppm` fast
+    pub const fn at_rate(reference: i64, synthetic: i64, ppm: i64) -> Self {
+        Self {
+            reference_offset: reference,
+            synthetic_offset: synthetic,
+            synthetic_ticks: (PPM + ppm) as u32,
+            reference_ticks: PPM as u32,
+        }
+    }
+
+    /// Synthetic time at `reference`
+    pub fn apply(&self, reference: i64) -> i64 {
+        let elapsed = reference as i128 - self.reference_offset as i128;
+        let scaled = elapsed * self.synthetic_ticks as i128 / self.reference_ticks as i128;
+        (self.synthetic_offset as i128 + scaled) as i64
+    }
+
+    /// Whether the clock advances
+    pub const fn is_running(&self) -> bool {
+        self.synthetic_ticks != 0
+    }
+
+    /// Rate relative to the reference, in parts per million
+    pub const fn rate_ppm(&self) -> i64 {
+        self.synthetic_ticks as i64 * PPM / self.reference_ticks as i64 - PPM
+    }
+}
+
+/// Arguments of [`Clock::update`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClockUpdate {
+    /// `update_flags` bits
+    pub flags: u32,
+
+    /// New rate, within `MAX_RATE_ADJUST_PPM`
+    pub rate_adjust_ppm: i32,
+
+    /// New value, in synthetic nanoseconds
+    pub value: i64,
+
+    /// Offset to slew by, in nanoseconds
+    pub slew_offset: i64,
+
+    /// New error bound, in nanoseconds
+    pub error_bound: u64,
+}
+
+/// Snapshot of a clock for `rx_clock_get_details`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClockDetails {
+    /// Creation options
+    pub options: u64,
+
+    /// Earliest time the clock reads
+    pub backstop_time: i64,
+
+    /// Bumped by every update
+    pub generation_counter: u32,
+
+    /// Reserved
+    pub padding: u32,
+
+    /// Error bound, or `ERROR_BOUND_UNKNOWN`
+    pub error_bound: u64,
+
+    /// Reference time the details were taken at
+    pub query_reference: i64,
+
+    /// Transform in effect
+    pub reference_to_synthetic: Transform,
+
+    /// Reference time a slew in progress ends, or 0
+    pub slew_end_reference: i64,
+
+    /// Transform from `slew_end_reference` on; equal to the current one
+    /// when not slewing
+    pub after_slew: Transform,
+
+    /// Reference time of the last value update, or 0
+    pub last_value_update: i64,
+
+    /// Reference time of the last rate update, or 0
+    pub last_rate_adjust_update: i64,
+
+    /// Reference time of the last error bound update, or 0
+    pub last_error_bound_update: i64,
+}
+
+/// A slew in progress
+#[derive(Debug, Clone, Copy)]
+struct Slew {
+    /// Reference time it ends
+    end: i64,
+
+    /// Transform from then on
+    after: Transform,
+}
+
+/// Mutable clock state
+#[derive(Debug, Clone, Copy)]
+struct ClockState {
+    transform: Transform,
+    slew: Option<Slew>,
+    error_bound: u64,
+    generation: u32,
+    last_value_update: i64,
+    last_rate_update: i64,
+    last_error_update: i64,
+}
+
+impl ClockState {
+    /// Transform in effect at `reference`
+    fn transform_at(&self, reference: i64) -> Transform {
+        match self.slew {
+            Some(slew) if reference >= slew.end => slew.after,
+            _ => self.transform,
+        }
+    }
+
+    /// Rate the clock returns to after any slew
+    fn base_rate_ppm(&self) -> i64 {
+        self.slew.map_or(self.transform, |slew| slew.after).rate_ppm()
+    }
+}
+
+/// A clock object
+pub struct Clock {
+    /// Object ID
+    pub id: ClockId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// `clock_opt` bits
+    pub options: u64,
+
+    /// Earliest time the clock reads
+    pub backstop: i64,
+
+    state: SeqLock<ClockState>,
+}
+
+/// Current reference (monotonic) time
+fn reference_now() -> i64 {
+    crate::kernel::timer::current_time() as i64
+}
+
+impl Clock {
+    /// Create a clock at reference time `now`
+    fn new(options: u64, backstop: i64, now: i64) -> Result<Self> {
+        if options & !clock_opt::ALL != 0 || backstop < 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let options = if options & clock_opt::CONTINUOUS != 0 {
+            options | clock_opt::MONOTONIC
+        } else {
+            options
+        };
+
+        let transform = if options & clock_opt::AUTO_START != 0 {
+            if backstop > now {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+            Transform::at_rate(now, now, 0)
+        } else {
+            Transform::stopped(backstop)
+        };
+
+        Ok(Self {
+            id: NEXT_CLOCK_ID.fetch_add(1, Ordering::Relaxed),
+            koid: alloc_koid(),
+            options,
+            backstop,
+            state: SeqLock::new(ClockState {
+                transform,
+                slew: None,
+                error_bound: ERROR_BOUND_UNKNOWN,
+                generation: 0,
+                last_value_update: 0,
+                last_rate_update: 0,
+                last_error_update: 0,
+            }),
+        })
+    }
+
+    /// Read the clock
+    pub fn read(&self) -> i64 {
+        self.read_at(reference_now())
+    }
+
+    /// Whether the clock has been started
+    pub fn is_started(&self) -> bool {
+        self.state.read().transform.is_running()
+    }
+
+    /// Snapshot the transform and update history
+    pub fn details(&self) -> ClockDetails {
+        self.details_at(reference_now())
+    }
+
+    /// Adjust the clock
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_INVALID_ARGS` - unknown or no flags, both a value and a
+    ///   slew, a rate or slew out of range, a value before the backstop, a
+    ///   backwards step of a `MONOTONIC` clock or any step of a started
+    ///   `CONTINUOUS` one
+    /// - `RX_ERR_BAD_STATE` - a rate or slew on a clock that isn't started
+    pub fn update(&self, args: &ClockUpdate) -> Result {
+        self.update_at(args, reference_now())
+    }
+
+    fn read_at(&self, reference: i64) -> i64 {
+        let value = self.state.read().transform_at(reference).apply(reference);
+        value.max(self.backstop)
+    }
+
+    fn details_at(&self, reference: i64) -> ClockDetails {
+        self.details_of(&self.state.read(), reference)
+    }
+
+    fn details_of(&self, state: &ClockState, reference: i64) -> ClockDetails {
+        let (slew_end, after) = match state.slew {
+            Some(slew) if reference < slew.end => (slew.end, slew.after),
+            _ => {
+                let current = state.transform_at(reference);
+                (0, current)
+            }
+        };
+
+        ClockDetails {
+            options: self.options,
+            backstop_time: self.backstop,
+            generation_counter: state.generation,
+            padding: 0,
+            error_bound: state.error_bound,
+            query_reference: reference,
+            reference_to_synthetic: state.transform_at(reference),
+            slew_end_reference: slew_end,
+            after_slew: after,
+            last_value_update: state.last_value_update,
+            last_rate_adjust_update: state.last_rate_update,
+            last_error_bound_update: state.last_error_update,
+        }
+    }
+
+    fn update_at(&self, args: &ClockUpdate, now: i64) -> Result {
+        let flags = args.flags;
+        if flags == 0 || flags & !update_flags::ALL != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let set_value = flags & update_flags::VALUE_VALID != 0;
+        let set_rate = flags & update_flags::RATE_ADJUST_VALID != 0;
+        let slew = flags & update_flags::SLEW_VALID != 0;
+        if set_value && slew {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if set_rate && args.rate_adjust_ppm.abs() > MAX_RATE_ADJUST_PPM {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if slew && args.slew_offset.abs() > MAX_SLEW_NS {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        self.state.write(|state| {
+            self.apply_update(state, args, now)?;
+            if system_utc() == Some(self.id) {
+                timepage::publish_utc(&self.details_of(state, now));
+            }
+            Ok(())
+        })
+    }
+
+    /// Apply validated `args` to `state`
+    fn apply_update(&self, state: &mut ClockState, args: &ClockUpdate, now: i64) -> Result {
+        let flags = args.flags;
+        let set_value = flags & update_flags::VALUE_VALID != 0;
+        let set_rate = flags & update_flags::RATE_ADJUST_VALID != 0;
+        let slew = flags & update_flags::SLEW_VALID != 0;
+        let started = state.transform.is_running();
+        if !started && !set_value && (set_rate || slew) {
+            return Err(RX_ERR_BAD_STATE);
+        }
+
+        // Fold a finished slew into the base transform
+        if let Some(done) = state.slew.filter(|s| now >= s.end) {
+            state.transform = done.after;
+            state.slew = None;
+        }
+        let current = state.transform.apply(now).max(self.backstop);
+
+        if set_value {
+            if args.value < self.backstop {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+            if started && self.options & clock_opt::CONTINUOUS != 0 {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+            if started && self.options & clock_opt::MONOTONIC != 0 && args.value < current {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+        }
+
+        let rate = if set_rate { args.rate_adjust_ppm as i64 } else if started { state.base_rate_ppm() } else { 0 };
+        if set_value || set_rate {
+            let value = if set_value { args.value } else { current };
+            state.transform = Transform::at_rate(now, value, rate);
+            state.slew = None;
+        }
+
+        if slew && args.slew_offset != 0 {
+            let from = state.transform.apply(now);
+            let direction = args.slew_offset.signum();
+            let duration = args.slew_offset.abs() * (PPM / SLEW_RATE_PPM);
+            let during = Transform::at_rate(now, from, rate + direction * SLEW_RATE_PPM);
+            let end = now + duration;
+            state.transform = during;
+            state.slew = Some(Slew { end, after: Transform::at_rate(end, during.apply(end), rate) });
+        }
+
+        if set_value {
+            state.last_value_update = now;
+        }
+        if set_rate {
+            state.last_rate_update = now;
+        }
+        if flags & update_flags::ERROR_BOUND_VALID != 0 {
+            state.error_bound = args.error_bound;
+            state.last_error_update = now;
+        }
+        state.generation = state.generation.wrapping_add(1);
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Registry
+/// ============================================================================
+
+/// Live clocks by ID
+static CLOCKS: SpinMutex<BTreeMap<ClockId, Arc<Clock>>> = SpinMutex::new(BTreeMap::new());
+
+/// The system UTC clock, which processes get unless told otherwise
+static SYSTEM_UTC: AtomicU64 = AtomicU64::new(0);
+
+/// Create and register a clock
+///
+/// # Errors
+///
+/// - `RX_ERR_INVALID_ARGS` - unknown options, a negative backstop, or
+///   `AUTO_START` with a backstop in the future
+pub fn create(options: u64, backstop: i64) -> Result<ClockId> {
+    let clock = Clock::new(options, backstop, reference_now())?;
+    let id = clock.id;
+    CLOCKS.lock().insert(id, Arc::new(clock));
+    Ok(id)
+}
+
+/// Look up a clock
+pub fn get(id: ClockId) -> Result<Arc<Clock>> {
+    CLOCKS.lock().get(&id).cloned().ok_or(RX_ERR_NOT_FOUND)
+}
+
+/// The system UTC clock, once [`init`] has created it
+pub fn system_utc() -> Option<ClockId> {
+    match SYSTEM_UTC.load(Ordering::Acquire) {
+        0 => None,
+        id => Some(id),
+    }
+}
+
+/// Create the system UTC clock
+///
+/// It is monotonic and starts unset: it reads the backstop until a time
+/// daemon sets it.
+pub fn init() {
+    if let Ok(id) = create(clock_opt::MONOTONIC, 0) {
+        SYSTEM_UTC.store(id, Ordering::Release);
+        if let Ok(clock) = get(id) {
+            timepage::publish_utc(&clock.details());
+        }
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(value: i64) -> ClockUpdate {
+        ClockUpdate { flags: update_flags::VALUE_VALID, value, ..Default::default() }
+    }
+
+    #[test]
+    fn test_transform() {
+        let t = Transform::at_rate(1000, 5000, 0);
+        assert_eq!(t.apply(1000), 5000);
+        assert_eq!(t.apply(3000), 7000);
+
+        let fast = Transform::at_rate(0, 0, 1000);
+        assert_eq!(fast.apply(1_000_000), 1_001_000);
+        assert_eq!(fast.rate_ppm(), 1000);
+        assert!(!Transform::stopped(7).is_running());
+        assert_eq!(Transform::stopped(7).apply(123), 7);
+    }
+
+    #[test]
+    fn test_start_and_step() {
+        let clock = Clock::new(clock_opt::MONOTONIC, 100, 0).unwrap();
+        assert_eq!(clock.read_at(50), 100);
+        assert_eq!(clock.update_at(&value(50), 10), Err(RX_ERR_INVALID_ARGS));
+
+        let rate = ClockUpdate { flags: update_flags::RATE_ADJUST_VALID, ..Default::default() };
+        assert_eq!(clock.update_at(&rate, 10), Err(RX_ERR_BAD_STATE));
+
+        clock.update_at(&value(1_000_000), 10).unwrap();
+        assert_eq!(clock.read_at(20), 1_000_010);
+
+        // Monotonic: forward steps only
+        assert_eq!(clock.update_at(&value(5), 20), Err(RX_ERR_INVALID_ARGS));
+        clock.update_at(&value(2_000_000), 20).unwrap();
+        assert_eq!(clock.details_at(20).generation_counter, 2);
+        assert_eq!(clock.details_at(20).last_value_update, 20);
+
+        let continuous = Clock::new(clock_opt::CONTINUOUS, 0, 0).unwrap();
+        assert_ne!(continuous.options & clock_opt::MONOTONIC, 0);
+        continuous.update_at(&value(1000), 0).unwrap();
+        assert_eq!(continuous.update_at(&value(5000), 1), Err(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_rate_and_slew() {
+        let clock = Clock::new(clock_opt::AUTO_START, 0, 0).unwrap();
+        assert_eq!(clock.read_at(1000), 1000);
+
+        let rate = ClockUpdate { flags: update_flags::RATE_ADJUST_VALID, rate_adjust_ppm: 100, ..Default::default() };
+        clock.update_at(&rate, 0).unwrap();
+        assert_eq!(clock.read_at(1_000_000), 1_000_100);
+
+        let too_fast = ClockUpdate { rate_adjust_ppm: 1001, ..rate };
+        assert_eq!(clock.update_at(&too_fast, 0), Err(RX_ERR_INVALID_ARGS));
+
+        // Slewing 1 ms forward takes 2 s at 500 ppm, then the rate returns
+        let slew = ClockUpdate { flags: update_flags::SLEW_VALID, slew_offset: 1_000_000, ..Default::default() };
+        let start = 1_000_000;
+        let before = clock.read_at(start);
+        clock.update_at(&slew, start).unwrap();
+
+        let details = clock.details_at(start);
+        assert_eq!(details.slew_end_reference, start + 2_000_000_000);
+        assert_eq!(details.reference_to_synthetic.rate_ppm(), 600);
+        assert_eq!(details.after_slew.rate_ppm(), 100);
+
+        let end = details.slew_end_reference;
+        let unslewed = before + (end - start) + (end - start) / 10_000;
+        assert_eq!(clock.read_at(end), unslewed + 1_000_000);
+        assert_eq!(clock.details_at(end).slew_end_reference, 0);
+        assert_eq!(clock.read_at(end + 1_000_000) - clock.read_at(end), 1_000_100);
+    }
+}
@@ -0,0 +1,459 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Core Dumps
+//!
+//! Writes a process whose thread is held at an exception into a VMO, so a
+//! crash handler can save it and debug the crash off-target.
+//!
+//! # Format
+//!
+//! Integers are little-endian and offsets are from the start of the dump.
+//! A [`CoreHeader`] is followed by a directory of [`StreamEntry`]s, then
+//! the streams they point at:
+//!
+//! - [`STREAM_EXCEPTION`] - The [`ExceptionReport`]
+//! - [`STREAM_THREAD`] - A [`ThreadRecord`] for the faulting thread. The
+//!   process's other threads aren't stopped, so they aren't recorded.
+//! - [`STREAM_MAPPINGS`] - A [`MappingRecord`] per mapping
+//! - [`STREAM_HANDLES`] - A `HandleTableRecord` per handle, as the
+//!   `HANDLE_TABLE` info topic returns them
+//! - [`STREAM_MEMORY`] - A [`MemoryRecord`] per saved range; the bytes
+//!   follow the streams
+//!
+//! # Memory
+//!
+//! The stack snapshot taken at the exception always comes first. From
+//! the mappings, only committed pages are saved, so dumping never
+//! allocates memory the process didn't touch: those of writable mappings,
+//! the page at the pc, and with [`COREDUMP_ALL_MEMORY`] those of every
+//! readable mapping. Physical VMOs (device memory) are never read. Saved
+//! memory stops at `kernel.coredump.max-bytes`, and the header is then
+//! marked [`CORE_FLAG_TRUNCATED`].
+//!
+//! All processes share the root user VMAR for now, so the mappings are
+//! those of the whole user address space.
+//!
+//! # Command Line
+//!
+//! - `kernel.coredump.max-bytes=<bytes>` - Most memory saved in one dump
+//!   (default 4 MiB)
+
+use crate::kernel::cmdline;
+use crate::kernel::object::exception::{ExceptionReport, GeneralRegs, Pending};
+use crate::kernel::object::koid::Koid;
+use crate::kernel::object::vmo::{Vmo, VmoFlags};
+use crate::kernel::process;
+use crate::kernel::syscalls::object::{self as object_syscalls, HandleTableRecord};
+use crate::kernel::syscalls::vmar::{self, VmarMapping};
+use crate::kernel::thread::current_thread_handle_table;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+// Import logging macros
+use crate::log_info;
+
+KCOUNTER!(COREDUMPS_WRITTEN, "kernel.coredumps.written");
+KCOUNTER!(COREDUMPS_TRUNCATED, "kernel.coredumps.truncated");
+
+/// First bytes of every dump
+pub const CORE_MAGIC: [u8; 8] = *b"RXCORE\0\0";
+
+/// Format version in [`CoreHeader::version`]
+pub const CORE_VERSION: u32 = 1;
+
+/// Header flag: saved memory stopped at the size limit
+pub const CORE_FLAG_TRUNCATED: u32 = 1 << 0;
+
+/// Stream: the [`ExceptionReport`]
+pub const STREAM_EXCEPTION: u32 = 1;
+
+/// Stream: [`ThreadRecord`]s
+pub const STREAM_THREAD: u32 = 2;
+
+/// Stream: [`MappingRecord`]s
+pub const STREAM_MAPPINGS: u32 = 3;
+
+/// Stream: `HandleTableRecord`s
+pub const STREAM_HANDLES: u32 = 4;
+
+/// Stream: [`MemoryRecord`]s
+pub const STREAM_MEMORY: u32 = 5;
+
+/// `rx_thread_write_coredump` option: save every readable mapping
+pub const COREDUMP_ALL_MEMORY: u32 = 1 << 0;
+
+/// Command line option for the memory limit
+const CMDLINE_MAX_BYTES: &str = "kernel.coredump.max-bytes";
+
+/// Default memory limit
+pub const DEFAULT_MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Mappings are saved a page at a time
+const PAGE_SIZE: usize = 4096;
+
+/// ============================================================================
+/// Records
+/// ============================================================================
+
+/// Start of a dump
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoreHeader {
+    /// [`CORE_MAGIC`]
+    pub magic: [u8; 8],
+
+    /// [`CORE_VERSION`]
+    pub version: u32,
+
+    /// `CORE_FLAG_*` flags
+    pub flags: u32,
+
+    /// Number of directory entries
+    pub stream_count: u32,
+
+    /// Reserved, zero
+    pub reserved: u32,
+
+    /// Offset of the directory
+    pub directory_offset: u64,
+
+    /// Size of the dump in bytes; the VMO is rounded up to pages
+    pub size: u64,
+}
+
+/// Directory entry for one stream
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StreamEntry {
+    /// `STREAM_*` kind
+    pub kind: u32,
+
+    /// Number of records in the stream
+    pub count: u32,
+
+    /// Offset of the stream
+    pub offset: u64,
+
+    /// Size of the stream in bytes
+    pub size: u64,
+}
+
+/// A thread and its registers
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRecord {
+    /// Thread ID
+    pub tid: u64,
+
+    /// Thread koid
+    pub koid: Koid,
+
+    /// Registers at the exception
+    pub regs: GeneralRegs,
+}
+
+/// A mapping in the process's address space
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MappingRecord {
+    /// Address of the mapping
+    pub base: u64,
+
+    /// Size of the mapping
+    pub size: u64,
+
+    /// Koid of the mapped VMO
+    pub vmo_koid: Koid,
+
+    /// Offset of the mapping in the VMO
+    pub vmo_offset: u64,
+
+    /// Protection: read 1, write 2, execute 4
+    pub prot: u32,
+
+    /// Reserved, zero
+    pub reserved: u32,
+}
+
+/// A saved range of memory
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryRecord {
+    /// Address of the range
+    pub base: u64,
+
+    /// Size of the range
+    pub size: u64,
+
+    /// Offset of the range's bytes in the dump
+    pub offset: u64,
+}
+
+/// Bytes of a record slice
+///
+/// Only for the `repr(C)` records above, which have no padding.
+fn as_bytes<T: Copy>(records: &[T]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(records.as_ptr() as *const u8, core::mem::size_of_val(records)) }
+}
+
+/// ============================================================================
+/// Memory Selection
+/// ============================================================================
+
+/// Where the bytes of a saved range come from
+enum Source {
+    /// The stack snapshot
+    Stack,
+
+    /// A VMO, from this offset
+    Vmo(Arc<Vmo>, usize),
+}
+
+/// A range of memory to save
+struct Range {
+    base: u64,
+    size: u64,
+    source: Source,
+}
+
+/// Choose the memory to save, up to `max_bytes`
+///
+/// Returns the ranges and whether `max_bytes` cut them short.
+fn select_memory(
+    regs: &GeneralRegs,
+    stack_len: usize,
+    mappings: &[VmarMapping],
+    options: u32,
+    max_bytes: u64,
+) -> (Vec<Range>, bool) {
+    let mut ranges = Vec::new();
+    let mut total = 0u64;
+
+    if stack_len > 0 {
+        ranges.push(Range { base: regs.sp, size: stack_len as u64, source: Source::Stack });
+        total += stack_len as u64;
+    }
+
+    for mapping in mappings {
+        if mapping.vmo.flags.is_physical() || !mapping.prot.can_read() {
+            continue;
+        }
+        let end = mapping.addr + mapping.size;
+        let (start, end) = if mapping.prot.can_write() || options & COREDUMP_ALL_MEMORY != 0 {
+            (mapping.addr, end)
+        } else if (mapping.addr..end).contains(&regs.pc) {
+            let page = regs.pc & !(PAGE_SIZE as u64 - 1);
+            (page, page + PAGE_SIZE as u64)
+        } else {
+            continue;
+        };
+
+        let first = (mapping.vmo_offset + (start - mapping.addr)) as usize / PAGE_SIZE;
+        let last = (mapping.vmo_offset + (end - mapping.addr)) as usize / PAGE_SIZE;
+        for (page, _) in mapping.vmo.pages.committed_in(first, last) {
+            if total + PAGE_SIZE as u64 > max_bytes {
+                return (ranges, true);
+            }
+            total += PAGE_SIZE as u64;
+
+            let offset = page * PAGE_SIZE;
+            let addr = mapping.addr + offset as u64 - mapping.vmo_offset;
+            if let Some(Range { base, size, source: Source::Vmo(vmo, from) }) = ranges.last_mut() {
+                if Arc::ptr_eq(vmo, &mapping.vmo) && *base + *size == addr && *from + *size as usize == offset {
+                    *size += PAGE_SIZE as u64;
+                    continue;
+                }
+            }
+            ranges.push(Range {
+                base: addr,
+                size: PAGE_SIZE as u64,
+                source: Source::Vmo(mapping.vmo.clone(), offset),
+            });
+        }
+    }
+    (ranges, false)
+}
+
+/// ============================================================================
+/// Writing
+/// ============================================================================
+
+/// Write a core dump of the process of a thread waiting for its handler
+///
+/// `options` are `rx_thread_write_coredump` options. Returns the dump in
+/// a new VMO.
+pub fn write(pending: &Pending, options: u32) -> Result<Vmo> {
+    if options & !COREDUMP_ALL_MEMORY != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    process::lookup(pending.report.pid).ok_or(RX_ERR_BAD_STATE)?;
+    let max_bytes = cmdline::cmdline_get_uint64(CMDLINE_MAX_BYTES, DEFAULT_MAX_BYTES);
+
+    let thread = ThreadRecord {
+        tid: pending.report.tid,
+        koid: pending.report.thread_koid,
+        regs: pending.regs,
+    };
+    let user_mappings = vmar::user_mappings();
+    let mappings: Vec<MappingRecord> = user_mappings
+        .iter()
+        .map(|m| MappingRecord {
+            base: m.addr,
+            size: m.size,
+            vmo_koid: m.vmo.koid,
+            vmo_offset: m.vmo_offset,
+            prot: m.prot as u32,
+            reserved: 0,
+        })
+        .collect();
+    // Every thread shares one handle table for now, as with HANDLE_TABLE info
+    let handles: Vec<HandleTableRecord> =
+        object_syscalls::table_records(current_thread_handle_table());
+    let (ranges, truncated) =
+        select_memory(&pending.regs, pending.stack.len(), &user_mappings, options, max_bytes);
+
+    // Lay out the streams after the directory, then the memory
+    let streams: [(u32, usize, &[u8]); 4] = [
+        (STREAM_EXCEPTION, 1, as_bytes(core::slice::from_ref(&pending.report))),
+        (STREAM_THREAD, 1, as_bytes(core::slice::from_ref(&thread))),
+        (STREAM_MAPPINGS, mappings.len(), as_bytes(&mappings)),
+        (STREAM_HANDLES, handles.len(), as_bytes(&handles)),
+    ];
+    let directory_offset = core::mem::size_of::<CoreHeader>();
+    let mut offset = directory_offset + (streams.len() + 1) * core::mem::size_of::<StreamEntry>();
+    let mut directory = Vec::with_capacity(streams.len() + 1);
+    for &(kind, count, bytes) in &streams {
+        directory.push(StreamEntry { kind, count: count as u32, offset: offset as u64, size: bytes.len() as u64 });
+        offset += bytes.len();
+    }
+    let memory_size = ranges.len() * core::mem::size_of::<MemoryRecord>();
+    directory.push(StreamEntry {
+        kind: STREAM_MEMORY,
+        count: ranges.len() as u32,
+        offset: offset as u64,
+        size: memory_size as u64,
+    });
+    offset += memory_size;
+    let memory: Vec<MemoryRecord> = ranges
+        .iter()
+        .map(|range| {
+            let record = MemoryRecord { base: range.base, size: range.size, offset: offset as u64 };
+            offset += range.size as usize;
+            record
+        })
+        .collect();
+
+    let header = CoreHeader {
+        magic: CORE_MAGIC,
+        version: CORE_VERSION,
+        flags: if truncated { CORE_FLAG_TRUNCATED } else { 0 },
+        stream_count: directory.len() as u32,
+        reserved: 0,
+        directory_offset: directory_offset as u64,
+        size: offset as u64,
+    };
+
+    let dump = Vmo::create(offset.div_ceil(PAGE_SIZE) * PAGE_SIZE, VmoFlags::empty)?;
+    let mut at = 0;
+    let mut put = |bytes: &[u8]| -> Result {
+        if !bytes.is_empty() {
+            at += dump.write(at, bytes)?;
+        }
+        Ok(())
+    };
+    put(as_bytes(core::slice::from_ref(&header)))?;
+    put(as_bytes(&directory))?;
+    for &(_, _, bytes) in &streams {
+        put(bytes)?;
+    }
+    put(as_bytes(&memory))?;
+
+    let mut page = vec![0u8; PAGE_SIZE];
+    for (range, record) in ranges.iter().zip(&memory) {
+        match &range.source {
+            Source::Stack => {
+                dump.write(record.offset as usize, &pending.stack)?;
+            }
+            Source::Vmo(vmo, from) => {
+                for done in (0..range.size as usize).step_by(PAGE_SIZE) {
+                    vmo.read(from + done, &mut page)?;
+                    dump.write(record.offset as usize + done, &page)?;
+                }
+            }
+        }
+    }
+
+    COREDUMPS_WRITTEN.add(1);
+    if truncated {
+        COREDUMPS_TRUNCATED.add(1);
+    }
+    log_info!(
+        "coredump: pid {} tid {}: {} bytes, {} ranges{}",
+        pending.report.pid,
+        pending.report.tid,
+        offset,
+        ranges.len(),
+        if truncated { " (truncated)" } else { "" }
+    );
+    Ok(dump)
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::vm::MemProt;
+
+    #[test]
+    fn test_record_layout() {
+        assert_eq!(core::mem::size_of::<CoreHeader>(), 40);
+        assert_eq!(core::mem::size_of::<StreamEntry>(), 24);
+        assert_eq!(core::mem::size_of::<ThreadRecord>(), 304);
+        assert_eq!(core::mem::size_of::<MappingRecord>(), 40);
+        assert_eq!(core::mem::size_of::<MemoryRecord>(), 24);
+    }
+
+    #[test]
+    fn test_select_memory() {
+        let data = Arc::new(Vmo::create(4 * PAGE_SIZE, VmoFlags::empty).unwrap());
+        data.write(0, &[1; 2 * PAGE_SIZE]).unwrap();
+        let code = Arc::new(Vmo::create(4 * PAGE_SIZE, VmoFlags::empty).unwrap());
+        code.write(0, &[2; 4 * PAGE_SIZE]).unwrap();
+        let mapping = |addr, vmo: &Arc<Vmo>, prot| VmarMapping {
+            addr,
+            size: 4 * PAGE_SIZE as u64,
+            vmo: vmo.clone(),
+            vmo_offset: 0,
+            prot,
+        };
+        let mappings = [mapping(0x10000, &data, MemProt::ReadWrite), mapping(0x20000, &code, MemProt::Read)];
+        let regs = GeneralRegs { pc: 0x21234, sp: 0x30000, ..GeneralRegs::default() };
+
+        // Committed pages of the writable mapping and the page at the pc
+        let (ranges, truncated) = select_memory(&regs, 0x100, &mappings, 0, u64::MAX);
+        let found: Vec<_> = ranges.iter().map(|r| (r.base, r.size)).collect();
+        assert_eq!(found, [(0x30000, 0x100), (0x10000, 0x2000), (0x21000, 0x1000)]);
+        assert!(!truncated);
+
+        let (ranges, _) = select_memory(&regs, 0, &mappings, COREDUMP_ALL_MEMORY, u64::MAX);
+        let found: Vec<_> = ranges.iter().map(|r| (r.base, r.size)).collect();
+        assert_eq!(found, [(0x10000, 0x2000), (0x20000, 0x4000)]);
+
+        let (ranges, truncated) = select_memory(&regs, 0x100, &mappings, 0, 0x1100);
+        assert_eq!(ranges.len(), 2);
+        assert!(truncated);
+    }
+}
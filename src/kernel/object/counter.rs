@@ -0,0 +1,217 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Counter Objects
+//!
+//! A counter holds a signed 64-bit value that any handle holder can add
+//! to, read or overwrite. It asserts `COUNTER_POSITIVE` while the value is
+//! above zero and `COUNTER_NON_POSITIVE` otherwise, so one process can
+//! count produced items up while another waits for them and counts them
+//! back down.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let counter = Counter::new();
+//! counter.add(1)?;
+//! assert_eq!(counter.signals(), COUNTER_POSITIVE);
+//! counter.add(-1)?;
+//! ```
+
+use crate::kernel::object::handle::ObjectType;
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::leak::Tracked;
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use core::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+
+/// ============================================================================
+/// Counter Signals
+/// ============================================================================
+
+/// Asserted while the value is above zero
+pub const COUNTER_POSITIVE: u64 = 0x10;
+
+/// Asserted while the value is zero or below
+pub const COUNTER_NON_POSITIVE: u64 = 0x20;
+
+/// ============================================================================
+/// Counter ID
+/// ============================================================================
+
+/// Counter identifier
+pub type CounterId = u64;
+
+/// Next counter ID counter
+static NEXT_COUNTER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a new counter ID
+fn alloc_counter_id() -> CounterId {
+    NEXT_COUNTER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// ============================================================================
+/// Counter
+/// ============================================================================
+
+/// Counter object
+pub struct Counter {
+    /// Counter ID
+    pub id: CounterId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// Current value
+    value: AtomicI64,
+
+    /// Reference count (one per handle)
+    pub ref_count: AtomicUsize,
+
+    /// Entry in the live object table (debug builds)
+    tracked: Tracked,
+}
+
+impl Counter {
+    /// Create a counter with value 0
+    pub fn new() -> Self {
+        let koid = alloc_koid();
+        Self {
+            id: alloc_counter_id(),
+            koid,
+            value: AtomicI64::new(0),
+            ref_count: AtomicUsize::new(1),
+            tracked: Tracked::new(koid, ObjectType::Counter),
+        }
+    }
+
+    /// Get counter ID
+    pub const fn id(&self) -> CounterId {
+        self.id
+    }
+
+    /// Get the kernel object ID
+    pub const fn koid(&self) -> Koid {
+        self.koid
+    }
+
+    /// Get the current value
+    pub fn read(&self) -> i64 {
+        self.value.load(Ordering::Acquire)
+    }
+
+    /// Replace the value
+    ///
+    /// Returns true if the signals changed.
+    pub fn write(&self, value: i64) -> bool {
+        let old = self.value.swap(value, Ordering::AcqRel);
+        Self::signals_for(old) != Self::signals_for(value)
+    }
+
+    /// Add `amount` to the value
+    ///
+    /// Fails with `RX_ERR_OUT_OF_RANGE`, leaving the value unchanged, if
+    /// the result would overflow. Returns true if the signals changed.
+    pub fn add(&self, amount: i64) -> Result<bool> {
+        let mut current = self.value.load(Ordering::Acquire);
+        loop {
+            let new = current.checked_add(amount).ok_or(RX_ERR_OUT_OF_RANGE)?;
+            match self.value.compare_exchange_weak(
+                current,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Ok(Self::signals_for(current) != Self::signals_for(new)),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Get the signals asserted for the current value
+    pub fn signals(&self) -> u64 {
+        Self::signals_for(self.read())
+    }
+
+    fn signals_for(value: i64) -> u64 {
+        if value > 0 {
+            COUNTER_POSITIVE
+        } else {
+            COUNTER_NON_POSITIVE
+        }
+    }
+
+    /// Increment reference count
+    pub fn ref_inc(&self) {
+        self.ref_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decrement reference count
+    ///
+    /// Returns true if this was the last reference.
+    pub fn ref_dec(&self) -> bool {
+        self.ref_count.fetch_sub(1, Ordering::Release) == 1
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_new() {
+        let counter = Counter::new();
+        assert_eq!(counter.read(), 0);
+        assert_eq!(counter.signals(), COUNTER_NON_POSITIVE);
+        assert_ne!(counter.id(), Counter::new().id());
+        assert_ne!(counter.koid(), Counter::new().koid());
+    }
+
+    #[test]
+    fn test_counter_add_transitions() {
+        let counter = Counter::new();
+
+        assert_eq!(counter.add(2), Ok(true));
+        assert_eq!(counter.signals(), COUNTER_POSITIVE);
+        assert_eq!(counter.add(-1), Ok(false));
+        assert_eq!(counter.add(-1), Ok(true));
+        assert_eq!(counter.signals(), COUNTER_NON_POSITIVE);
+        assert_eq!(counter.add(-1), Ok(false));
+        assert_eq!(counter.read(), -1);
+    }
+
+    #[test]
+    fn test_counter_add_overflow() {
+        let counter = Counter::new();
+        counter.write(i64::MAX);
+
+        assert_eq!(counter.add(1), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(counter.read(), i64::MAX);
+
+        counter.write(i64::MIN);
+        assert_eq!(counter.add(-1), Err(RX_ERR_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn test_counter_write() {
+        let counter = Counter::new();
+
+        assert!(counter.write(5));
+        assert!(!counter.write(7));
+        assert!(counter.write(0));
+        assert_eq!(counter.read(), 0);
+    }
+}
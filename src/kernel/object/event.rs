@@ -26,6 +26,9 @@
 //! ```
 
 
+use crate::kernel::object::handle::ObjectType;
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::leak::Tracked;
 use crate::kernel::sync::wait_queue::WaitQueue;
 use crate::kernel::sync::Mutex;
 use crate::rustux::types::*;
@@ -91,6 +94,9 @@ pub struct Event {
     /// Event ID
     pub id: EventId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Current signal state
     pub signaled: AtomicBool,
 
@@ -102,6 +108,9 @@ pub struct Event {
 
     /// Reference count
     pub ref_count: AtomicUsize,
+
+    /// Entry in the live object table (debug builds)
+    tracked: Tracked,
 }
 
 impl Event {
@@ -112,12 +121,15 @@ impl Event {
     /// * `signaled` - Initial signal state
     /// * `flags` - Event flags
     pub fn new(signaled: bool, flags: EventFlags) -> Self {
+        let koid = alloc_koid();
         Self {
             id: alloc_event_id(),
+            koid,
             signaled: AtomicBool::new(signaled),
             flags,
             waiters: Mutex::new(WaitQueue::new()),
             ref_count: AtomicUsize::new(1),
+            tracked: Tracked::new(koid, ObjectType::Event),
         }
     }
 
@@ -126,6 +138,11 @@ impl Event {
         self.id
     }
 
+    /// Get the kernel object ID
+    pub const fn koid(&self) -> Koid {
+        self.koid
+    }
+
     /// Check if event is signaled
     pub fn is_signaled(&self) -> bool {
         self.signaled.load(Ordering::Acquire)
@@ -223,64 +240,107 @@ fn alloc_eventpair_id() -> EventPairId {
 
 /// Event pair
 ///
-/// A pair of events that signal each other.
-/// When one event is signaled, the other becomes unsignaled.
+/// One side of a pair of linked objects. Each side carries its own user
+/// signal bits, which either side can set: through its own handle with
+/// `object_signal`, or on the other side with `object_signal_peer`. When
+/// the last handle to one side closes, the other side is marked peer
+/// closed and no longer accepts peer signals.
 pub struct EventPair {
     /// Event pair ID
     pub id: EventPairId,
 
-    /// First event
-    pub event_a: Event,
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// Koid of the other side
+    pub peer_koid: Koid,
 
-    /// Second event
-    pub event_b: Event,
+    /// Current signal bits of this side
+    pub signals: AtomicU64,
+
+    /// The other side has lost its last handle
+    pub peer_closed: AtomicBool,
 
     /// Peer reference (for notification)
     pub peer: AtomicUsize,
 
-    /// Reference count
+    /// Reference count (one per handle)
     pub ref_count: AtomicUsize,
+
+    /// Entry in the live object table (debug builds)
+    tracked: Tracked,
 }
 
 impl EventPair {
     /// Create an event pair
     ///
-    /// Returns a pair of event IDs.
+    /// Returns both sides, each naming the other as its peer.
     pub fn create() -> Result<(Self, Self)> {
         let id_a = alloc_eventpair_id();
         let id_b = alloc_eventpair_id();
+        let koid_a = alloc_koid();
+        let koid_b = alloc_koid();
 
-        let pair_a = Self {
-            id: id_a,
-            event_a: Event::new(false, EventFlags::empty),
-            event_b: Event::new(false, EventFlags::empty),
-            peer: AtomicUsize::new(id_b as usize),
-            ref_count: AtomicUsize::new(1),
-        };
+        Ok((Self::new(id_a, koid_a, id_b, koid_b), Self::new(id_b, koid_b, id_a, koid_a)))
+    }
 
-        let pair_b = Self {
-            id: id_b,
-            event_a: Event::new(false, EventFlags::empty),
-            event_b: Event::new(false, EventFlags::empty),
-            peer: AtomicUsize::new(id_a as usize),
+    fn new(id: EventPairId, koid: Koid, peer: EventPairId, peer_koid: Koid) -> Self {
+        Self {
+            id,
+            koid,
+            peer_koid,
+            signals: AtomicU64::new(0),
+            peer_closed: AtomicBool::new(false),
+            peer: AtomicUsize::new(peer as usize),
             ref_count: AtomicUsize::new(1),
-        };
+            tracked: Tracked::new(koid, ObjectType::EventPair),
+        }
+    }
 
-        Ok((pair_a, pair_b))
+    /// Get the kernel object ID
+    pub const fn koid(&self) -> Koid {
+        self.koid
     }
 
-    /// Signal this event (and unsignal peer)
-    pub fn signal(&self) {
-        // Signal our event
-        self.event_a.signal();
+    /// Get the ID of the other side
+    pub fn peer_id(&self) -> EventPairId {
+        self.peer.load(Ordering::Acquire) as EventPairId
+    }
+
+    /// Get the current signal bits
+    pub fn signals(&self) -> u64 {
+        self.signals.load(Ordering::Acquire)
+    }
 
-        // Unsignal peer's event
-        // In real implementation, would notify peer
+    /// Clear then set signal bits
+    ///
+    /// Returns the signal bits after the update.
+    pub fn update_signals(&self, clear_mask: u64, set_mask: u64) -> u64 {
+        let mut current = self.signals.load(Ordering::Acquire);
+        loop {
+            let new = (current & !clear_mask) | set_mask;
+            match self.signals.compare_exchange_weak(
+                current,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return new,
+                Err(actual) => current = actual,
+            }
+        }
     }
 
-    /// Wait for this event to be signaled
-    pub fn wait(&self) -> Result {
-        self.event_a.wait(None)
+    /// Check if the other side has lost its last handle
+    pub fn is_peer_closed(&self) -> bool {
+        self.peer_closed.load(Ordering::Acquire)
+    }
+
+    /// Mark the other side as closed
+    ///
+    /// Returns false if it was already marked.
+    pub fn set_peer_closed(&self) -> bool {
+        !self.peer_closed.swap(true, Ordering::AcqRel)
     }
 
     /// Increment reference count
@@ -339,4 +399,31 @@ mod tests {
         let (pair_a, pair_b) = EventPair::create().unwrap();
         assert_ne!(pair_a.id, pair_b.id);
     }
+
+    #[test]
+    fn test_eventpair_peers() {
+        let (pair_a, pair_b) = EventPair::create().unwrap();
+        assert_eq!(pair_a.peer_id(), pair_b.id);
+        assert_eq!(pair_b.peer_id(), pair_a.id);
+        assert_eq!(pair_a.signals(), 0);
+        assert!(!pair_a.is_peer_closed());
+    }
+
+    #[test]
+    fn test_eventpair_update_signals() {
+        let (pair, _peer) = EventPair::create().unwrap();
+        assert_eq!(pair.update_signals(0, 0x0300_0000), 0x0300_0000);
+        assert_eq!(pair.update_signals(0x0100_0000, 0), 0x0200_0000);
+
+        // Clear is applied before set
+        assert_eq!(pair.update_signals(0x0200_0000, 0x0200_0000), 0x0200_0000);
+    }
+
+    #[test]
+    fn test_eventpair_peer_closed() {
+        let (pair, _peer) = EventPair::create().unwrap();
+        assert!(pair.set_peer_closed());
+        assert!(pair.is_peer_closed());
+        assert!(!pair.set_peer_closed());
+    }
 }
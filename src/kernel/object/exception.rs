@@ -0,0 +1,538 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Exception Channels
+//!
+//! Lets a userspace handler see a thread's fatal exception (fault,
+//! breakpoint, bad instruction) before its process is killed.
+//!
+//! # Design
+//!
+//! - **Binding**: `rx_task_create_exception_channel` binds a channel to a
+//!   job. The kernel keeps one end and the caller gets the other; a job
+//!   has at most one handler.
+//! - **Dispatch**: A thread that takes an exception in user mode looks for
+//!   a handler on its process's job, then on each parent job up to the
+//!   root. It snapshots its registers and the top of its stack, writes an
+//!   [`ExceptionReport`] to the handler and waits for the handler to
+//!   resume it, or for [`RESUME_TIMEOUT`] to pass.
+//! - **Thread state**: While a thread waits, `rx_thread_read_state` on it
+//!   returns the snapshot, `rx_thread_write_coredump` dumps its process
+//!   (see [`coredump`](super::coredump)) and `rx_thread_resume` releases
+//!   it.
+//! - **Debug exceptions**: Breakpoints, watchpoints and single steps are
+//!   decoded from DR6, ESR_EL1 or scause into a [`DebugEvent`], reported
+//!   as [`EXCP_SW_BREAKPOINT`] or [`EXCP_HW_BREAKPOINT`] with a subtype
+//!   and the raw status register. Hardware breakpoints are armed per
+//!   thread with `rx_thread_write_state(THREAD_STATE_DEBUG_REGS)`.
+//! - **Outcome**: Handlers only observe. A released thread's process is
+//!   killed with [`TASK_RETCODE_EXCEPTION_KILL`], as it would be with no
+//!   handler.
+
+use crate::kernel::arch::arch_traits::ArchDebug;
+use crate::kernel::object::channel::ChannelId;
+use crate::kernel::object::job::{self, JobId, TASK_RETCODE_EXCEPTION_KILL};
+use crate::kernel::object::koid::Koid;
+use crate::kernel::process;
+use crate::kernel::sched;
+use crate::kernel::sync::event::{Event, EventFlags};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::syscalls::channel;
+use crate::kernel::thread::{self, Thread, ThreadId};
+use crate::kernel::timer;
+use crate::kernel::usercopy::{copy_from_user, UserPtr};
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+KCOUNTER!(EXCEPTIONS_DISPATCHED, "kernel.exceptions.dispatched");
+KCOUNTER!(EXCEPTIONS_TIMED_OUT, "kernel.exceptions.timed_out");
+
+/// How long a thread waits for its handler to resume it
+pub const RESUME_TIMEOUT: u64 = 30_000_000_000;
+
+/// Most bytes of the stack saved for the handler
+pub const STACK_SNAPSHOT_SIZE: usize = 8192;
+
+/// `rx_thread_read_state` kind: [`GeneralRegs`]
+pub const THREAD_STATE_GENERAL_REGS: u32 = 0;
+
+/// `rx_thread_read_state` kind: the stack snapshot, starting at `sp`
+pub const THREAD_STATE_STACK: u32 = 1;
+
+/// `rx_thread_read_state` and `rx_thread_write_state` kind: [`DebugRegs`]
+pub const THREAD_STATE_DEBUG_REGS: u32 = 2;
+
+/// Exception kind: hardware breakpoint, watchpoint or single step
+pub const EXCP_HW_BREAKPOINT: u32 = 0x1;
+
+/// Exception kind: breakpoint instruction
+pub const EXCP_SW_BREAKPOINT: u32 = 0x2;
+
+/// [`EXCP_HW_BREAKPOINT`] subtype: single step
+pub const HW_BREAKPOINT_SINGLE_STEP: u32 = 1;
+
+/// [`EXCP_HW_BREAKPOINT`] subtype: instruction breakpoint
+pub const HW_BREAKPOINT_INSTRUCTION: u32 = 2;
+
+/// [`EXCP_HW_BREAKPOINT`] subtype: data watchpoint
+pub const HW_BREAKPOINT_WATCHPOINT: u32 = 3;
+
+/// Most hardware breakpoints [`DebugRegs`] holds
+pub const MAX_HW_BREAKPOINTS: usize = 4;
+
+/// Page size used to copy the stack a page at a time
+const PAGE_SIZE: usize = 4096;
+
+/// ============================================================================
+/// Reports
+/// ============================================================================
+
+/// Registers of a thread at its exception
+///
+/// `regs` holds the general registers in the architecture's DWARF order
+/// (amd64: rax, rdx, rcx, rbx, rsi, rdi, rbp, rsp, r8-r15; arm64: x0-x30,
+/// sp; riscv64: x0-x31).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GeneralRegs {
+    /// Program counter
+    pub pc: u64,
+
+    /// Stack pointer
+    pub sp: u64,
+
+    /// Frame pointer
+    pub fp: u64,
+
+    /// Flags register
+    pub flags: u64,
+
+    /// General registers
+    pub regs: [u64; 32],
+}
+
+/// Message written to the handler for each exception
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExceptionReport {
+    /// `ZX_EXCP_*` exception kind
+    pub kind: u32,
+
+    /// `HW_BREAKPOINT_*` for [`EXCP_HW_BREAKPOINT`], otherwise 0
+    pub subtype: u32,
+
+    /// Process ID, also its handle value
+    pub pid: u64,
+
+    /// Thread ID, also its handle value
+    pub tid: u64,
+
+    /// Process koid
+    pub process_koid: Koid,
+
+    /// Thread koid
+    pub thread_koid: Koid,
+
+    /// Program counter
+    pub pc: u64,
+
+    /// Stack pointer
+    pub sp: u64,
+
+    /// Frame pointer
+    pub fp: u64,
+
+    /// Faulting address for page faults, otherwise 0
+    pub fault_addr: u64,
+
+    /// Architecture error code
+    pub err_code: u64,
+
+    /// For debug exceptions, the status register that was decoded: DR6
+    /// on amd64 (0 for `int3`), ESR_EL1 on arm64, scause on riscv64.
+    /// Otherwise 0.
+    pub arch_status: u64,
+}
+
+impl ExceptionReport {
+    fn as_bytes(&self) -> &[u8] {
+        // Plain `repr(C)` integers with no padding
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+/// Hardware breakpoints of a thread
+///
+/// Breakpoints are instruction breakpoints on user addresses. On write,
+/// `count` and `status` are ignored.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugRegs {
+    /// Breakpoint addresses
+    pub hw_bps: [u64; MAX_HW_BREAKPOINTS],
+
+    /// Bit `i` set: `hw_bps[i]` is armed
+    pub enabled: u32,
+
+    /// Breakpoints this CPU has for user threads, up to
+    /// [`MAX_HW_BREAKPOINTS`]; 0 if it has none the kernel can use
+    pub count: u32,
+
+    /// DR6 at the thread's last debug exception on amd64, otherwise 0
+    pub status: u64,
+}
+
+/// ============================================================================
+/// Debug Exceptions
+/// ============================================================================
+
+/// DR6: single step
+const DR6_BS: u64 = 1 << 14;
+
+/// ESR_EL1 exception classes
+const EC_BKPT_AARCH32: u64 = 0x38;
+const EC_BRK: u64 = 0x3c;
+const EC_BREAKPOINT_LOWER: u64 = 0x30;
+const EC_BREAKPOINT_SAME: u64 = 0x31;
+const EC_STEP_LOWER: u64 = 0x32;
+const EC_STEP_SAME: u64 = 0x33;
+const EC_WATCHPOINT_LOWER: u64 = 0x34;
+const EC_WATCHPOINT_SAME: u64 = 0x35;
+
+/// scause: breakpoint (ebreak, or a trigger)
+const SCAUSE_BREAKPOINT: u64 = 3;
+
+/// What stopped a thread at a debug exception
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugEvent {
+    /// A breakpoint instruction: int3, brk or ebreak
+    SoftwareBreakpoint,
+
+    /// Hardware instruction breakpoint
+    HardwareBreakpoint,
+
+    /// Hardware data watchpoint
+    Watchpoint,
+
+    /// Single step
+    SingleStep,
+}
+
+impl DebugEvent {
+    /// Decode a #DB from DR6
+    ///
+    /// DR7 tells instruction breakpoints (R/W bits 00) from watchpoints.
+    pub fn from_dr6(dr6: u64, dr7: u64) -> Option<Self> {
+        if dr6 & DR6_BS != 0 {
+            return Some(Self::SingleStep);
+        }
+        let index = (0..MAX_HW_BREAKPOINTS).find(|&i| dr6 & (1 << i) != 0)?;
+        if (dr7 >> (16 + index * 4)) & 0b11 == 0 {
+            Some(Self::HardwareBreakpoint)
+        } else {
+            Some(Self::Watchpoint)
+        }
+    }
+
+    /// Decode a synchronous exception from ESR_EL1
+    pub fn from_esr(esr: u64) -> Option<Self> {
+        match (esr >> 26) & 0x3f {
+            EC_BRK | EC_BKPT_AARCH32 => Some(Self::SoftwareBreakpoint),
+            EC_BREAKPOINT_LOWER | EC_BREAKPOINT_SAME => Some(Self::HardwareBreakpoint),
+            EC_STEP_LOWER | EC_STEP_SAME => Some(Self::SingleStep),
+            EC_WATCHPOINT_LOWER | EC_WATCHPOINT_SAME => Some(Self::Watchpoint),
+            _ => None,
+        }
+    }
+
+    /// Decode an exception from scause
+    ///
+    /// The kernel arms no triggers, so every breakpoint is an `ebreak`.
+    pub fn from_scause(scause: u64) -> Option<Self> {
+        match scause {
+            SCAUSE_BREAKPOINT => Some(Self::SoftwareBreakpoint),
+            _ => None,
+        }
+    }
+
+    /// Report kind and subtype
+    pub fn kind(self) -> (u32, u32) {
+        match self {
+            Self::SoftwareBreakpoint => (EXCP_SW_BREAKPOINT, 0),
+            Self::HardwareBreakpoint => (EXCP_HW_BREAKPOINT, HW_BREAKPOINT_INSTRUCTION),
+            Self::Watchpoint => (EXCP_HW_BREAKPOINT, HW_BREAKPOINT_WATCHPOINT),
+            Self::SingleStep => (EXCP_HW_BREAKPOINT, HW_BREAKPOINT_SINGLE_STEP),
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+type Arch = crate::kernel::arch::amd64::Amd64Arch;
+
+#[cfg(target_arch = "aarch64")]
+type Arch = crate::kernel::arch::arm64::Arm64Arch;
+
+#[cfg(target_arch = "riscv64")]
+type Arch = crate::kernel::arch::riscv64::Riscv64Arch;
+
+/// The hardware breakpoints of `thread`
+pub fn debug_regs(thread: &Thread) -> DebugRegs {
+    let mut regs = DebugRegs {
+        count: Arch::user_breakpoint_count(),
+        ..DebugRegs::default()
+    };
+    Arch::read_user_breakpoints(thread, &mut regs);
+    regs
+}
+
+/// Replace the hardware breakpoints of `thread`
+///
+/// They take effect the next time the thread is switched in. Fails with
+/// `RX_ERR_NOT_SUPPORTED` if the CPU has none the kernel can use (riscv64
+/// has no triggers it can program from S-mode without SBI support).
+pub fn set_debug_regs(thread: &Thread, regs: &DebugRegs) -> Result {
+    if Arch::user_breakpoint_count() == 0 {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    if unsafe { Arch::write_user_breakpoints(thread, regs) } < 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    Ok(())
+}
+
+/// ============================================================================
+/// Handlers
+/// ============================================================================
+
+/// Kernel end of each job's exception channel
+static HANDLERS: SpinMutex<BTreeMap<JobId, ChannelId>> = SpinMutex::new(BTreeMap::new());
+
+/// Bind a new exception channel to `job_id`
+///
+/// Returns the handler's end.
+pub fn bind(job_id: JobId) -> Result<ChannelId> {
+    let mut handlers = HANDLERS.lock();
+    if handlers.contains_key(&job_id) {
+        return Err(RX_ERR_ALREADY_EXISTS);
+    }
+    let (kernel_end, user_end) = channel::create_pair()?;
+    handlers.insert(job_id, kernel_end);
+    log_info!("exception: job {} handled through channel {}", job_id, user_end);
+    Ok(user_end)
+}
+
+/// Kernel end of the nearest handler for a process in `job_id`
+fn find_handler(job_id: JobId) -> Option<ChannelId> {
+    let handlers = HANDLERS.lock();
+    let mut job = job::lookup(job_id);
+    while let Some(current) = job {
+        if let Some(&channel) = handlers.get(&current.id) {
+            return Some(channel);
+        }
+        job = current.parent.and_then(|parent| job::lookup(unsafe { (*parent).id }));
+    }
+    None
+}
+
+/// ============================================================================
+/// Waiting Threads
+/// ============================================================================
+
+/// A thread waiting for its handler
+pub struct Pending {
+    /// Report written to the handler
+    pub report: ExceptionReport,
+
+    /// Registers at the exception
+    pub regs: GeneralRegs,
+
+    /// Stack from `regs.sp` up, as much as was mapped
+    pub stack: Vec<u8>,
+
+    /// Signaled by `rx_thread_resume`
+    resumed: Event,
+}
+
+/// Threads waiting for their handler, by thread ID
+static PENDING: SpinMutex<BTreeMap<ThreadId, Arc<Pending>>> = SpinMutex::new(BTreeMap::new());
+
+/// The snapshot of `tid`, if it is waiting for its handler
+pub fn pending(tid: ThreadId) -> Option<Arc<Pending>> {
+    PENDING.lock().get(&tid).cloned()
+}
+
+/// Release `tid` from waiting for its handler
+pub fn resume(tid: ThreadId) -> Result {
+    let pending = pending(tid).ok_or(RX_ERR_BAD_STATE)?;
+    pending.resumed.signal();
+    Ok(())
+}
+
+/// Copy up to [`STACK_SNAPSHOT_SIZE`] bytes of user stack from `sp`
+///
+/// Stops at the first page that can't be read, which is normally the
+/// top of the stack.
+fn snapshot_stack(sp: u64) -> Vec<u8> {
+    let mut stack = vec![0u8; STACK_SNAPSHOT_SIZE];
+    let mut copied = 0;
+    while copied < STACK_SNAPSHOT_SIZE {
+        let addr = sp as usize + copied;
+        let len = (PAGE_SIZE - addr % PAGE_SIZE).min(STACK_SNAPSHOT_SIZE - copied);
+        let dst = stack[copied..].as_mut_ptr();
+        if unsafe { copy_from_user(dst, UserPtr::<u8>::new(addr), len) }.is_err() {
+            break;
+        }
+        copied += len;
+    }
+    stack.truncate(copied);
+    stack
+}
+
+/// ============================================================================
+/// Dispatch
+/// ============================================================================
+
+/// Hand the current thread's exception to its handler
+///
+/// Called by the architecture's fault handlers for exceptions taken in
+/// user mode, with interrupts enabled. Returns false if no job up to the
+/// root has a handler; otherwise the current process is killed and this
+/// doesn't return.
+pub fn dispatch(kind: u32, regs: &GeneralRegs, fault_addr: u64, err_code: u64) -> bool {
+    deliver(kind, 0, regs, fault_addr, err_code, 0)
+}
+
+/// Hand the current thread's debug exception to its handler
+///
+/// As [`dispatch`]. `fault_addr` is the data address for watchpoints and
+/// `arch_status` the register `event` was decoded from.
+pub fn dispatch_debug(event: DebugEvent, regs: &GeneralRegs, fault_addr: u64, arch_status: u64) -> bool {
+    let (kind, subtype) = event.kind();
+    deliver(kind, subtype, regs, fault_addr, 0, arch_status)
+}
+
+fn deliver(
+    kind: u32,
+    subtype: u32,
+    regs: &GeneralRegs,
+    fault_addr: u64,
+    err_code: u64,
+    arch_status: u64,
+) -> bool {
+    let Some(current) = thread::get_current_thread() else {
+        return false;
+    };
+    let Some(process) = current.pid().and_then(process::lookup) else {
+        return false;
+    };
+    let Some(handler) = find_handler(process.job_id) else {
+        return false;
+    };
+
+    let report = ExceptionReport {
+        kind,
+        subtype,
+        pid: process.pid(),
+        tid: current.tid,
+        process_koid: process.koid(),
+        thread_koid: current.koid,
+        pc: regs.pc,
+        sp: regs.sp,
+        fp: regs.fp,
+        fault_addr,
+        err_code,
+        arch_status,
+    };
+    let pending = Arc::new(Pending {
+        report,
+        regs: *regs,
+        stack: snapshot_stack(regs.sp),
+        resumed: Event::new(false, EventFlags::empty()),
+    });
+
+    PENDING.lock().insert(current.tid, pending.clone());
+    EXCEPTIONS_DISPATCHED.add(1);
+    match channel::write_kernel_message(handler, report.as_bytes()) {
+        Ok(_) => {
+            let deadline = timer::current_time().saturating_add(RESUME_TIMEOUT);
+            if pending.resumed.wait_deadline(deadline).is_err() {
+                EXCEPTIONS_TIMED_OUT.add(1);
+                log_warn!("exception: handler didn't resume tid {} in time", current.tid);
+            }
+        }
+        Err(err) => {
+            log_warn!("exception: can't report tid {} to its handler: {:?}", current.tid, err);
+        }
+    }
+    PENDING.lock().remove(&current.tid);
+
+    // Die as the process would have with no handler
+    let pid = process.pid();
+    process.exit(TASK_RETCODE_EXCEPTION_KILL);
+    if let Some(job) = job::lookup(process.job_id) {
+        job.remove_process(pid as u32);
+    }
+    process::remove(pid);
+    sched::exit_current(TASK_RETCODE_EXCEPTION_KILL);
+    true
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_layout() {
+        assert_eq!(core::mem::size_of::<ExceptionReport>(), 88);
+        assert_eq!(core::mem::size_of::<GeneralRegs>(), 288);
+        assert_eq!(core::mem::size_of::<DebugRegs>(), 48);
+    }
+
+    #[test]
+    fn test_decode_debug_events() {
+        // DR6.BS, DR6.B1 with DR7 R/W1 execute, then write
+        assert_eq!(DebugEvent::from_dr6(DR6_BS, 0), Some(DebugEvent::SingleStep));
+        assert_eq!(DebugEvent::from_dr6(0b10, 0b100), Some(DebugEvent::HardwareBreakpoint));
+        assert_eq!(DebugEvent::from_dr6(0b10, 0b01 << 20), Some(DebugEvent::Watchpoint));
+        assert_eq!(DebugEvent::from_dr6(0, 0), None);
+
+        assert_eq!(DebugEvent::from_esr(0xf200_0000), Some(DebugEvent::SoftwareBreakpoint));
+        assert_eq!(DebugEvent::from_esr(EC_BREAKPOINT_LOWER << 26), Some(DebugEvent::HardwareBreakpoint));
+        assert_eq!(DebugEvent::from_esr(EC_STEP_LOWER << 26), Some(DebugEvent::SingleStep));
+        assert_eq!(DebugEvent::from_esr(EC_WATCHPOINT_LOWER << 26), Some(DebugEvent::Watchpoint));
+        assert_eq!(DebugEvent::from_esr(0x9200_0000), None);
+
+        assert_eq!(DebugEvent::from_scause(3), Some(DebugEvent::SoftwareBreakpoint));
+        assert_eq!(DebugEvent::from_scause(13), None);
+
+        assert_eq!(DebugEvent::SingleStep.kind(), (EXCP_HW_BREAKPOINT, HW_BREAKPOINT_SINGLE_STEP));
+        assert_eq!(DebugEvent::SoftwareBreakpoint.kind(), (EXCP_SW_BREAKPOINT, 0));
+    }
+
+    #[test]
+    fn test_resume_needs_pending() {
+        assert_eq!(resume(ThreadId::MAX), Err(RX_ERR_BAD_STATE));
+    }
+}
@@ -25,6 +25,7 @@
 //! ```
 
 
+use crate::kernel::object::koid::{alloc_koid, Koid, KOID_INVALID};
 use crate::kernel::sync::Mutex;
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
@@ -77,6 +78,12 @@ impl Rights {
     /// Apply profile to thread
     pub const APPLY_PROFILE: Self = Self(0x100);
 
+    /// Signal the peer of a paired object
+    pub const SIGNAL_PEER: Self = Self(0x200);
+
+    /// Change the size of a VMO
+    pub const RESIZE: Self = Self(0x400);
+
     /// Basic rights (READ | WRITE)
     pub const BASIC: Self = Self(0x03);
 
@@ -152,11 +159,15 @@ impl Rights {
             ObjectType::Vmar => Self::MAP | Self::READ | Self::WRITE,
             ObjectType::Channel => Self::READ | Self::WRITE,
             ObjectType::Event => Self::SIGNAL | Self::WAIT,
-            ObjectType::EventPair => Self::SIGNAL | Self::WAIT,
+            ObjectType::EventPair => Self::SIGNAL | Self::SIGNAL_PEER | Self::WAIT,
             ObjectType::Timer => Self::SIGNAL | Self::WRITE,
             ObjectType::Job => Self::MANAGE,
             ObjectType::Port => Self::READ | Self::WRITE,
             ObjectType::Profile => Self::READ,
+            ObjectType::Resource => Self::DUPLICATE | Self::TRANSFER,
+            ObjectType::Counter => {
+                Self::READ | Self::WRITE | Self::WAIT | Self::DUPLICATE | Self::TRANSFER
+            }
             ObjectType::Unknown => Self::NONE,
         }
     }
@@ -264,6 +275,12 @@ pub enum ObjectType {
 
     /// Profile object
     Profile = 11,
+
+    /// Resource object
+    Resource = 12,
+
+    /// Counter object
+    Counter = 13,
 }
 
 impl ObjectType {
@@ -281,6 +298,8 @@ impl ObjectType {
             9 => Self::Job,
             10 => Self::Port,
             11 => Self::Profile,
+            12 => Self::Resource,
+            13 => Self::Counter,
             _ => Self::Unknown,
         }
     }
@@ -305,6 +324,8 @@ impl ObjectType {
             Self::Job => "job",
             Self::Port => "port",
             Self::Profile => "profile",
+            Self::Resource => "resource",
+            Self::Counter => "counter",
         }
     }
 }
@@ -320,6 +341,9 @@ pub struct KernelObjectBase {
     /// Object type
     pub obj_type: ObjectType,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Reference count
     pub ref_count: AtomicUsize,
 
@@ -328,10 +352,16 @@ pub struct KernelObjectBase {
 }
 
 impl KernelObjectBase {
-    /// Create a new kernel object base
-    pub const fn new(obj_type: ObjectType) -> Self {
+    /// Create a new kernel object base with a fresh koid
+    pub fn new(obj_type: ObjectType) -> Self {
+        Self::with_koid(obj_type, alloc_koid())
+    }
+
+    /// Create a kernel object base for an object that already has a koid
+    pub const fn with_koid(obj_type: ObjectType, koid: Koid) -> Self {
         Self {
             obj_type,
+            koid,
             ref_count: AtomicUsize::new(1),
             destroying: AtomicBool::new(false),
         }
@@ -433,6 +463,15 @@ impl Handle {
         }
     }
 
+    /// Get the koid of the object the handle refers to
+    pub fn koid(&self) -> Koid {
+        if self.base.is_null() {
+            KOID_INVALID
+        } else {
+            unsafe { (*self.base).koid }
+        }
+    }
+
     /// Check if handle is valid
     pub fn is_valid(&self) -> bool {
         !self.base.is_null() && !self.rights.is_none()
@@ -670,6 +709,19 @@ impl HandleTable {
         }
     }
 
+    /// Take a handle out of the table without closing it
+    ///
+    /// Used to move a handle into a channel message.
+    pub fn take(&self, handle_val: u32) -> Result<Handle> {
+        if handle_val as usize >= MAX_HANDLES {
+            return Err(RX_ERR_BAD_HANDLE);
+        }
+
+        let handle = self.slots[handle_val as usize].lock().take().ok_or(RX_ERR_BAD_HANDLE)?;
+        *self.count.lock() -= 1;
+        Ok(handle)
+    }
+
     /// Duplicate a handle in the table
     pub fn duplicate(&self, handle_val: u32, mask: Rights) -> Result<u32> {
         let handle = {
@@ -688,6 +740,16 @@ impl HandleTable {
         self.add(handle)
     }
 
+    /// Get the values of all handles in the table
+    pub fn handle_values(&self) -> alloc::vec::Vec<u32> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.lock().is_some())
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
     /// Get handle count
     pub fn count(&self) -> usize {
         *self.count.lock()
@@ -747,6 +809,7 @@ mod tests {
         // Create a dummy object
         let obj_base = KernelObjectBase::new(ObjectType::Event);
         let handle = Handle::new(&obj_base as *const _, Rights::DEFAULT);
+        assert_eq!(handle.koid(), obj_base.koid);
 
         // Add to table
         let hv = table.add(handle).unwrap();
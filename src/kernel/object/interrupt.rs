@@ -0,0 +1,317 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Interrupt Objects
+//!
+//! Interrupt objects let userspace drivers receive device interrupts.
+//! A driver binds an object to a platform interrupt vector (or creates a
+//! virtual one), waits on it, and acknowledges each delivery.
+//!
+//! # Design
+//!
+//! - **Lock-free delivery**: The IRQ path only touches a static per-vector
+//!   table of atomics, never the object registry
+//! - **Coalescing**: Interrupts that arrive before the driver waits are
+//!   merged into one pending delivery carrying the latest timestamp
+//! - **Exclusive binding**: A physical vector can back at most one object
+//! - **Virtual interrupts**: Triggered from software instead of hardware
+//!
+//! # Usage
+//!
+//! ```rust
+//! let id = interrupt::create(vector, InterruptFlags::empty)?;
+//! // IRQ path:
+//! interrupt::dispatch(vector);
+//! // Driver:
+//! let timestamp = interrupt::get(id)?.wait()?;
+//! interrupt::get(id)?.ack()?;
+//! ```
+
+
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// ============================================================================
+/// Interrupt ID
+/// ============================================================================
+
+/// Interrupt object identifier
+pub type InterruptId = u64;
+
+/// Next interrupt ID counter
+static NEXT_INTERRUPT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a new interrupt ID
+fn alloc_interrupt_id() -> InterruptId {
+    NEXT_INTERRUPT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// ============================================================================
+/// Interrupt Flags
+/// ============================================================================
+
+/// Interrupt creation flags
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptFlags(pub u32);
+
+impl InterruptFlags {
+    /// No flags
+    pub const empty: Self = Self(0);
+
+    /// Virtual interrupt (triggered by software)
+    pub const VIRTUAL: Self = Self(0x01);
+
+    /// Create from raw value
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    /// Get raw value
+    pub const fn into_raw(self) -> u32 {
+        self.0
+    }
+
+    /// Check if virtual
+    pub const fn is_virtual(self) -> bool {
+        (self.0 & Self::VIRTUAL.0) != 0
+    }
+}
+
+/// ============================================================================
+/// Vector Table
+/// ============================================================================
+
+/// Number of platform interrupt vectors that can be bound
+///
+/// Covers the GIC SPI range and the PLIC source range.
+pub const MAX_IRQ_VECTORS: usize = 1024;
+
+/// Delivery state for one interrupt source
+struct VectorState {
+    /// Whether an object is bound to this source
+    bound: AtomicBool,
+
+    /// Whether a delivery is waiting to be consumed
+    pending: AtomicBool,
+
+    /// Timestamp of the latest delivery
+    timestamp: AtomicU64,
+}
+
+impl VectorState {
+    const fn new() -> Self {
+        Self {
+            bound: AtomicBool::new(false),
+            pending: AtomicBool::new(false),
+            timestamp: AtomicU64::new(0),
+        }
+    }
+
+    fn fire(&self, timestamp: u64) {
+        self.timestamp.store(timestamp, Ordering::Relaxed);
+        self.pending.store(true, Ordering::Release);
+    }
+
+    fn take(&self) -> Option<u64> {
+        if self.pending.swap(false, Ordering::Acquire) {
+            Some(self.timestamp.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+}
+
+const VECTOR_INIT: VectorState = VectorState::new();
+
+/// Per-vector delivery state, touched from IRQ context
+static VECTORS: [VectorState; MAX_IRQ_VECTORS] = [VECTOR_INIT; MAX_IRQ_VECTORS];
+
+/// Deliver a platform interrupt
+///
+/// Called from the architecture IRQ path. Returns true if an interrupt
+/// object owns the vector, in which case the caller should only EOI.
+pub fn dispatch(vector: u32) -> bool {
+    let state = match VECTORS.get(vector as usize) {
+        Some(state) => state,
+        None => return false,
+    };
+    if !state.bound.load(Ordering::Acquire) {
+        return false;
+    }
+    state.fire(crate::kernel::timer::current_time());
+    true
+}
+
+/// ============================================================================
+/// Interrupt
+/// ============================================================================
+
+/// Interrupt object
+pub struct Interrupt {
+    /// Interrupt ID
+    pub id: InterruptId,
+
+    /// Platform vector (unused for virtual interrupts)
+    pub vector: u32,
+
+    /// Creation flags
+    pub flags: InterruptFlags,
+
+    /// Delivery state for virtual interrupts
+    virt: VectorState,
+
+    /// Set once destroyed; waits fail with RX_ERR_CANCELED
+    destroyed: AtomicBool,
+}
+
+impl Interrupt {
+    /// Create an interrupt object
+    ///
+    /// Physical objects must be registered with `create` before they
+    /// receive deliveries.
+    pub fn new(vector: u32, flags: InterruptFlags) -> Result<Self> {
+        if !flags.is_virtual() && vector as usize >= MAX_IRQ_VECTORS {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        Ok(Self {
+            id: alloc_interrupt_id(),
+            vector: if flags.is_virtual() { 0 } else { vector },
+            flags,
+            virt: VectorState::new(),
+            destroyed: AtomicBool::new(false),
+        })
+    }
+
+    fn state(&self) -> &VectorState {
+        if self.flags.is_virtual() {
+            &self.virt
+        } else {
+            &VECTORS[self.vector as usize]
+        }
+    }
+
+    /// Consume a pending delivery
+    ///
+    /// Returns the delivery timestamp, or `RX_ERR_SHOULD_WAIT` if nothing
+    /// has arrived since the last wait.
+    pub fn wait(&self) -> Result<u64> {
+        if self.destroyed.load(Ordering::Acquire) {
+            return Err(RX_ERR_CANCELED);
+        }
+        self.state().take().ok_or(RX_ERR_SHOULD_WAIT)
+    }
+
+    /// Acknowledge the last delivery and re-arm the source
+    pub fn ack(&self) -> Result {
+        if self.destroyed.load(Ordering::Acquire) {
+            return Err(RX_ERR_CANCELED);
+        }
+        Ok(())
+    }
+
+    /// Trigger a virtual interrupt
+    pub fn trigger(&self, timestamp: u64) -> Result {
+        if !self.flags.is_virtual() {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        if self.destroyed.load(Ordering::Acquire) {
+            return Err(RX_ERR_CANCELED);
+        }
+        self.virt.fire(timestamp);
+        Ok(())
+    }
+
+    /// Check if a delivery is pending
+    pub fn is_pending(&self) -> bool {
+        self.state().pending.load(Ordering::Acquire)
+    }
+}
+
+/// ============================================================================
+/// Registry
+/// ============================================================================
+
+/// Live interrupt objects by ID
+static INTERRUPTS: SpinMutex<BTreeMap<InterruptId, Arc<Interrupt>>> = SpinMutex::new(BTreeMap::new());
+
+/// Create and register an interrupt object
+///
+/// Fails with `RX_ERR_ALREADY_EXISTS` if the vector is already bound.
+pub fn create(vector: u32, flags: InterruptFlags) -> Result<InterruptId> {
+    let irq = Interrupt::new(vector, flags)?;
+    let id = irq.id;
+
+    let mut interrupts = INTERRUPTS.lock();
+    if !flags.is_virtual() {
+        let state = &VECTORS[vector as usize];
+        if state.bound.swap(true, Ordering::AcqRel) {
+            return Err(RX_ERR_ALREADY_EXISTS);
+        }
+        // Drop anything that arrived while the vector was unowned
+        state.pending.store(false, Ordering::Release);
+    }
+    interrupts.insert(id, Arc::new(irq));
+    Ok(id)
+}
+
+/// Look up an interrupt object
+pub fn get(id: InterruptId) -> Result<Arc<Interrupt>> {
+    INTERRUPTS.lock().get(&id).cloned().ok_or(RX_ERR_NOT_FOUND)
+}
+
+/// Destroy an interrupt object and release its vector
+pub fn destroy(id: InterruptId) -> Result {
+    let irq = INTERRUPTS.lock().remove(&id).ok_or(RX_ERR_NOT_FOUND)?;
+    irq.destroyed.store(true, Ordering::Release);
+    if !irq.flags.is_virtual() {
+        VECTORS[irq.vector as usize].bound.store(false, Ordering::Release);
+    }
+    Ok(())
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_interrupt() {
+        let irq = Interrupt::new(0, InterruptFlags::VIRTUAL).unwrap();
+        assert_eq!(irq.wait(), Err(RX_ERR_SHOULD_WAIT));
+
+        irq.trigger(10).unwrap();
+        irq.trigger(20).unwrap();
+        assert_eq!(irq.wait(), Ok(20));
+        assert_eq!(irq.wait(), Err(RX_ERR_SHOULD_WAIT));
+    }
+
+    #[test]
+    fn test_physical_binding() {
+        let vector = 1000;
+        assert!(!dispatch(vector));
+
+        let id = create(vector, InterruptFlags::empty).unwrap();
+        assert_eq!(create(vector, InterruptFlags::empty), Err(RX_ERR_ALREADY_EXISTS));
+        assert!(get(id).unwrap().trigger(0).is_err());
+
+        VECTORS[vector as usize].fire(5);
+        assert_eq!(get(id).unwrap().wait(), Ok(5));
+
+        let irq = get(id).unwrap();
+        destroy(id).unwrap();
+        assert_eq!(irq.wait(), Err(RX_ERR_CANCELED));
+        assert!(!dispatch(vector));
+    }
+}
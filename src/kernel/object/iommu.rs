@@ -0,0 +1,132 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! IOMMU Objects
+//!
+//! An IOMMU object stands for one IOMMU driver (see
+//! [`dev::iommu`](crate::kernel::dev::iommu)) and is what BTIs are
+//! created against. Creating the first Intel or SMMUv3 object enables the
+//! hardware; from then on devices can only DMA through BTIs.
+//!
+//! A dummy IOMMU hands out physical addresses, so it can only be created
+//! while no hardware IOMMU is enabled.
+
+use crate::kernel::dev::iommu::{self, DummyIommu, Iommu};
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// IOMMU object identifier
+pub type IommuId = u64;
+
+/// Next IOMMU ID counter
+static NEXT_IOMMU_ID: AtomicU64 = AtomicU64::new(1);
+
+/// IOMMU hardware kind
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IommuKind {
+    /// No translation
+    Dummy = 0,
+
+    /// Intel VT-d
+    Intel = 1,
+
+    /// Arm SMMUv3
+    ArmSmmu = 2,
+}
+
+impl IommuKind {
+    /// Convert from the `rx_iommu_create` type argument
+    pub const fn try_from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Dummy),
+            1 => Some(Self::Intel),
+            2 => Some(Self::ArmSmmu),
+            _ => None,
+        }
+    }
+}
+
+/// An IOMMU object
+pub struct IommuObject {
+    /// Object ID
+    pub id: IommuId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    /// Hardware kind
+    pub kind: IommuKind,
+
+    driver: Arc<dyn Iommu>,
+}
+
+impl IommuObject {
+    /// The driver BTIs map through
+    pub fn driver(&self) -> Arc<dyn Iommu> {
+        self.driver.clone()
+    }
+}
+
+/// Whether a translating IOMMU has been enabled
+pub fn hardware_enabled() -> bool {
+    iommu::intel::is_enabled() || iommu::smmu::is_enabled()
+}
+
+/// Live IOMMU objects by ID
+static IOMMUS: SpinMutex<BTreeMap<IommuId, Arc<IommuObject>>> = SpinMutex::new(BTreeMap::new());
+
+/// Create and register an IOMMU object
+///
+/// # Errors
+///
+/// - `RX_ERR_NOT_FOUND` - the firmware describes no such hardware
+/// - `RX_ERR_NOT_SUPPORTED` - the hardware lacks a feature the driver needs
+/// - `RX_ERR_BAD_STATE` - a dummy IOMMU was asked for while a hardware
+///   IOMMU is enabled
+pub fn create(kind: IommuKind) -> Result<IommuId> {
+    let driver: Arc<dyn Iommu> = match kind {
+        IommuKind::Dummy if hardware_enabled() => return Err(RX_ERR_BAD_STATE),
+        IommuKind::Dummy => Arc::new(DummyIommu::new()),
+        IommuKind::Intel => iommu::intel::get_or_init()?,
+        IommuKind::ArmSmmu => iommu::smmu::get_or_init()?,
+    };
+
+    let id = NEXT_IOMMU_ID.fetch_add(1, Ordering::Relaxed);
+    IOMMUS.lock().insert(id, Arc::new(IommuObject { id, koid: alloc_koid(), kind, driver }));
+    Ok(id)
+}
+
+/// Look up an IOMMU object
+pub fn get(id: IommuId) -> Result<Arc<IommuObject>> {
+    IOMMUS.lock().get(&id).cloned().ok_or(RX_ERR_NOT_FOUND)
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_dummy() {
+        if hardware_enabled() {
+            return;
+        }
+        let id = create(IommuKind::Dummy).unwrap();
+        let iommu = get(id).unwrap();
+        assert_eq!(iommu.kind, IommuKind::Dummy);
+        assert!(!iommu.driver().translates());
+        assert_eq!(IommuKind::try_from_raw(3), None);
+    }
+}
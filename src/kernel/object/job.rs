@@ -16,6 +16,14 @@
 //! - **Accounting**: Track resource usage across all child processes
 //! - **Lifecycle**: Jobs are created explicitly and destroyed when all children exit
 //!
+//! # Out of Memory
+//!
+//! When memory runs out, the OOM killer picks one job with kill-on-OOM set
+//! (`JOB_KILL_ON_OOM` property) and kills it: the lowest importance
+//! (`JOB_IMPORTANCE` property) first, then the one using the most memory,
+//! then the newest. Jobs without kill-on-OOM, and the root job, are never
+//! chosen.
+//!
 //! # Usage
 //!
 //! ```rust
@@ -25,13 +33,17 @@
 //! ```
 
 
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::process;
 use crate::kernel::sync::Mutex;
+use crate::kernel::thread::{RuntimeTotals, TaskRuntimeInfo};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
 use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 
 /// ============================================================================
 /// Job ID
@@ -54,6 +66,18 @@ fn alloc_job_id() -> JobId {
     NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed)
 }
 
+/// Return code of processes killed by `rx_task_kill` or a CPU time limit
+pub const TASK_RETCODE_SYSCALL_KILL: rx_status_t = -1024;
+
+/// Return code of processes killed by the OOM killer
+pub const TASK_RETCODE_OOM_KILL: rx_status_t = -1028;
+
+/// Return code of processes killed by a fatal exception
+pub const TASK_RETCODE_EXCEPTION_KILL: rx_status_t = -1025;
+
+/// Importance of a new job
+pub const JOB_IMPORTANCE_DEFAULT: u32 = 100;
+
 /// ============================================================================
 /// Job Policy
 /// ============================================================================
@@ -323,6 +347,9 @@ pub struct Job {
     /// Job ID
     pub id: JobId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Parent job (None for root job)
     pub parent: Option<*const Job>,
 
@@ -346,6 +373,15 @@ pub struct Job {
 
     /// Reference count
     pub ref_count: AtomicUsize,
+
+    /// Runtime of processes and child jobs that have left the job
+    pub exited_runtime: RuntimeTotals,
+
+    /// Whether the OOM killer may kill this job
+    pub kill_on_oom: AtomicBool,
+
+    /// OOM kill order; the least important job is killed first
+    pub importance: AtomicU32,
 }
 
 unsafe impl Send for Job {}
@@ -356,6 +392,7 @@ impl Job {
     pub fn new_root() -> Arc<Self> {
         Arc::new(Self {
             id: JOB_ID_ROOT,
+            koid: alloc_koid(),
             parent: None,
             children: Mutex::new(BTreeSet::new()),
             processes: Mutex::new(BTreeSet::new()),
@@ -364,6 +401,9 @@ impl Job {
             stats: Mutex::new(JobStats::new()),
             killed: AtomicBool::new(false),
             ref_count: AtomicUsize::new(1),
+            exited_runtime: RuntimeTotals::new(),
+            kill_on_oom: AtomicBool::new(false),
+            importance: AtomicU32::new(JOB_IMPORTANCE_DEFAULT),
         })
     }
 
@@ -381,6 +421,7 @@ impl Job {
 
         let job = Arc::new(Self {
             id,
+            koid: alloc_koid(),
             parent: Some(Arc::as_ptr(parent)),
             children: Mutex::new(BTreeSet::new()),
             processes: Mutex::new(BTreeSet::new()),
@@ -389,6 +430,9 @@ impl Job {
             stats: Mutex::new(JobStats::new()),
             killed: AtomicBool::new(false),
             ref_count: AtomicUsize::new(1),
+            exited_runtime: RuntimeTotals::new(),
+            kill_on_oom: AtomicBool::new(false),
+            importance: AtomicU32::new(JOB_IMPORTANCE_DEFAULT),
         });
 
         // Add to parent's children
@@ -403,6 +447,11 @@ impl Job {
         self.id
     }
 
+    /// Get the kernel object ID
+    pub const fn koid(&self) -> Koid {
+        self.koid
+    }
+
     /// Check if this job has been killed
     pub fn is_killed(&self) -> bool {
         self.killed.load(Ordering::Acquire)
@@ -410,13 +459,51 @@ impl Job {
 
     /// Kill this job and all children
     pub fn kill(&self) {
-        self.killed.store(true, Ordering::Release);
+        self.kill_with_code(TASK_RETCODE_SYSCALL_KILL);
+    }
 
-        // TODO: Kill all child processes recursively
-        // This would require iterating through all processes
-        // and sending them a kill signal
+    /// Kill this job and all children, exiting every process with `code`
+    ///
+    /// Killing a job that was already killed does nothing.
+    pub fn kill_with_code(&self, code: rx_status_t) {
+        if self.killed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        // Exit and remove each process, as rx_task_kill does
+        let pids: Vec<u32> = self.processes.lock().iter().copied().collect();
+        for pid in pids {
+            if let Some(process) = process::lookup(pid as process::ProcessId) {
+                process.exit(code);
+            }
+            self.remove_process(pid);
+            process::remove(pid as process::ProcessId);
+        }
 
-        // For now, just mark the job as killed
+        let children: Vec<JobId> = self.children.lock().iter().copied().collect();
+        for child in children.into_iter().filter_map(lookup) {
+            child.kill_with_code(code);
+        }
+    }
+
+    /// Let the OOM killer kill this job, or not
+    pub fn set_kill_on_oom(&self, kill_on_oom: bool) {
+        self.kill_on_oom.store(kill_on_oom, Ordering::Release);
+    }
+
+    /// Whether the OOM killer may kill this job
+    pub fn kill_on_oom(&self) -> bool {
+        self.kill_on_oom.load(Ordering::Acquire)
+    }
+
+    /// Set the job's importance
+    pub fn set_importance(&self, importance: u32) {
+        self.importance.store(importance, Ordering::Relaxed);
+    }
+
+    /// The job's importance
+    pub fn importance(&self) -> u32 {
+        self.importance.load(Ordering::Relaxed)
     }
 
     /// Add a process to this job
@@ -447,9 +534,14 @@ impl Job {
     }
 
     /// Remove a process from this job
+    ///
+    /// The process's runtime stays counted in the job's.
     pub fn remove_process(&self, pid: u32) {
         if self.processes.lock().remove(&pid) {
             self.stats.lock().process_count -= 1;
+            if let Some(process) = process::lookup(pid as process::ProcessId) {
+                self.exited_runtime.add(&process.runtime());
+            }
         }
     }
 
@@ -460,12 +552,34 @@ impl Job {
     }
 
     /// Remove a child job
+    ///
+    /// The child's runtime stays counted in this job's.
     pub fn remove_child(&self, child_id: JobId) {
         if self.children.lock().remove(&child_id) {
             self.stats.lock().child_count -= 1;
+            if let Some(child) = lookup(child_id) {
+                self.exited_runtime.add(&child.runtime());
+            }
         }
     }
 
+    /// CPU time, queue time and context switches of every process in the
+    /// job and its descendants, past and present
+    pub fn runtime(&self) -> TaskRuntimeInfo {
+        let mut info = self.exited_runtime.get();
+        for &pid in self.processes.lock().iter() {
+            if let Some(process) = process::lookup(pid as process::ProcessId) {
+                info.add(&process.runtime());
+            }
+        }
+
+        let children: Vec<JobId> = self.children.lock().iter().copied().collect();
+        for child in children.into_iter().filter_map(lookup) {
+            info.add(&child.runtime());
+        }
+        info
+    }
+
     /// Check if an operation is allowed by policy
     pub fn check_policy(&self, operation: JobPolicy) -> bool {
         let policy = *self.policy.lock();
@@ -529,10 +643,11 @@ impl Job {
         let limits = *self.limits.lock();
 
         stats.cpu_time += delta_ns;
+        let exceeded = limits.max_cpu_time != 0 && stats.cpu_time > limits.max_cpu_time;
+        drop(stats);
 
-        // Check if CPU time limit exceeded
-        if limits.max_cpu_time != 0 && stats.cpu_time > limits.max_cpu_time {
-            // Kill the job when CPU limit exceeded
+        // Kill the job when CPU limit exceeded; killing takes the stats lock
+        if exceeded {
             self.kill();
         }
     }
@@ -618,6 +733,10 @@ impl JobRegistry {
     fn count(&self) -> usize {
         self.count.load(Ordering::Relaxed)
     }
+
+    fn iter(&self) -> impl Iterator<Item = &Arc<Job>> {
+        self.entries.iter().flatten()
+    }
 }
 
 /// Global job registry
@@ -658,6 +777,16 @@ pub fn lookup(id: JobId) -> Option<Arc<Job>> {
     }
 }
 
+/// Look up a job by koid
+///
+/// Walks every job, so this is for debuggers rather than hot paths.
+pub fn lookup_by_koid(koid: Koid) -> Option<Arc<Job>> {
+    if let Some(root) = get_root_job().filter(|root| root.koid == koid) {
+        return Some(root.clone());
+    }
+    JOB_REGISTRY.lock().iter().find(|job| job.koid == koid).cloned()
+}
+
 /// Register a job
 pub fn register(job: Arc<Job>) -> Result {
     JOB_REGISTRY.lock().insert(job)
@@ -673,6 +802,46 @@ pub fn count() -> usize {
     JOB_REGISTRY.lock().count()
 }
 
+/// A job the OOM killer may kill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OomCandidate {
+    /// Job ID
+    pub id: JobId,
+
+    /// Job importance
+    pub importance: u32,
+
+    /// Memory charged to the job in bytes
+    pub memory_used: u64,
+}
+
+/// Every live job with kill-on-OOM set
+pub fn oom_candidates() -> Vec<OomCandidate> {
+    JOB_REGISTRY
+        .lock()
+        .iter()
+        .filter(|job| job.kill_on_oom() && !job.is_killed())
+        .map(|job| OomCandidate {
+            id: job.id,
+            importance: job.importance(),
+            memory_used: job.stats.lock().memory_used,
+        })
+        .collect()
+}
+
+/// Pick the job to kill: least important, then largest, then newest
+pub fn select_oom_victim(candidates: &[OomCandidate]) -> Option<JobId> {
+    candidates
+        .iter()
+        .min_by(|a, b| {
+            a.importance
+                .cmp(&b.importance)
+                .then(b.memory_used.cmp(&a.memory_used))
+                .then(b.id.cmp(&a.id))
+        })
+        .map(|candidate| candidate.id)
+}
+
 /// ============================================================================
 /// Tests
 /// ============================================================================
@@ -743,4 +912,33 @@ mod tests {
         let result = child.add_process(1234);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_job_kill_children() {
+        let root = Job::new_root();
+        let parent = Job::new_child(&root, 0).unwrap();
+        let child = Job::new_child(&parent, 0).unwrap();
+        register(child.clone()).unwrap();
+
+        parent.kill_with_code(TASK_RETCODE_OOM_KILL);
+        assert!(child.is_killed());
+        unregister(child.id).unwrap();
+    }
+
+    #[test]
+    fn test_select_oom_victim() {
+        let candidate = |id, importance, memory_used| OomCandidate { id, importance, memory_used };
+
+        assert_eq!(select_oom_victim(&[]), None);
+
+        // Least important first, whatever its size
+        let candidates = [candidate(2, 100, 1 << 30), candidate(3, 10, 1 << 20), candidate(4, 50, 1 << 30)];
+        assert_eq!(select_oom_victim(&candidates), Some(3));
+
+        // Then the largest, then the newest
+        let candidates = [candidate(2, 10, 1 << 20), candidate(3, 10, 1 << 30), candidate(4, 10, 1 << 20)];
+        assert_eq!(select_oom_victim(&candidates), Some(3));
+        let candidates = [candidate(2, 10, 0), candidate(5, 10, 0), candidate(4, 10, 0)];
+        assert_eq!(select_oom_victim(&candidates), Some(5));
+    }
 }
@@ -0,0 +1,66 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Object IDs
+//!
+//! Every kernel object gets a koid when it is created. Koids come from one
+//! global counter, so they are unique across object types and never
+//! reused while the system is up. Debuggers use them to match handles to
+//! objects and to find a channel or eventpair's peer.
+//!
+//! Objects keep their per-type IDs (PIDs, channel IDs, ...) as registry
+//! keys; the koid is a separate, system-wide name for the same object.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let a = alloc_koid();
+//! let b = alloc_koid();
+//! assert!(b > a);
+//! ```
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Kernel object ID
+pub type Koid = u64;
+
+/// Koid that names no object
+pub const KOID_INVALID: Koid = 0;
+
+/// First koid handed out; lower values are reserved
+pub const KOID_FIRST: Koid = 1024;
+
+/// Next koid counter
+static NEXT_KOID: AtomicU64 = AtomicU64::new(KOID_FIRST);
+
+/// Allocate a new koid
+pub fn alloc_koid() -> Koid {
+    NEXT_KOID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The koid the next object will get
+///
+/// Every object created from now on has a koid at least this big.
+pub fn next_koid() -> Koid {
+    NEXT_KOID.load(Ordering::Relaxed)
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_koid_increasing() {
+        let a = alloc_koid();
+        let b = alloc_koid();
+        assert!(a >= KOID_FIRST);
+        assert!(b > a);
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Live Object Tracking
+//!
+//! Finds kernel objects that are never freed because a reference count or
+//! handle was never dropped. Debug builds only; in release builds
+//! [`Tracked`] is empty and nothing is recorded.
+//!
+//! # Design
+//!
+//! - **Tracking**: Each dispatcher object (event, eventpair, channel,
+//!   timer, counter, VMO) holds a [`Tracked`], which records the object's
+//!   koid, type, creating process and creation backtrace in a table, and
+//!   takes it out again when the object is dropped.
+//! - **Leaked handles**: A handle whose process has exited can never be
+//!   closed. An object still alive after the process that created it is
+//!   gone is reported as leaked; objects the kernel creates for itself
+//!   never are.
+//! - **Baselines**: Koids only go up, so everything created after a point
+//!   is the live objects with a koid at or above [`koid::next_koid`] at
+//!   that point. The `ktest` runner uses this to check a run frees all it
+//!   created.
+//!
+//! # Command Line
+//!
+//! - `kernel.leakcheck.interval-ms=<ms>` - How often to audit for leaked
+//!   objects in the background (default 60000, 0 disables)
+
+use crate::kernel::cmdline;
+use crate::kernel::lib::backtrace::Backtrace;
+use crate::kernel::object::handle::ObjectType;
+use crate::kernel::object::koid::{self, Koid, KOID_INVALID};
+use crate::kernel::process;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::timer;
+use crate::kernel::workqueue::{self, Work};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+// Import logging macros
+use crate::{log_info, log_warn};
+
+KCOUNTER!(LEAK_AUDITS, "kernel.leak.audits");
+KCOUNTER!(LEAK_FOUND, "kernel.leak.found");
+
+/// Command line option for the background audit interval
+const CMDLINE_INTERVAL: &str = "kernel.leakcheck.interval-ms";
+
+/// Default background audit interval
+pub const DEFAULT_INTERVAL_MS: u64 = 60_000;
+
+/// Whether objects are tracked in this build
+pub const ENABLED: bool = cfg!(debug_assertions);
+
+/// ============================================================================
+/// Live Objects
+/// ============================================================================
+
+/// A tracked object that hasn't been dropped
+#[derive(Clone)]
+pub struct LiveObject {
+    /// Object koid
+    pub koid: Koid,
+
+    /// Object type
+    pub obj_type: ObjectType,
+
+    /// Koid of the process that created the object, or `KOID_INVALID` if
+    /// the kernel did
+    pub owner: Koid,
+
+    /// When the object was created, in nanoseconds
+    pub created: u64,
+
+    /// Where the object was created
+    pub created_by: Backtrace,
+}
+
+impl LiveObject {
+    /// Whether the object outlived the process that created it
+    pub fn is_orphaned(&self) -> bool {
+        self.owner != KOID_INVALID
+            && !process::lookup_by_koid(self.owner).is_some_and(|p| p.state().is_alive())
+    }
+
+    fn print(&self) {
+        crate::println!(
+            "  koid {} {} owner {} age {} ms",
+            self.koid,
+            self.obj_type.name(),
+            self.owner,
+            self.age_ms()
+        );
+        self.created_by.print();
+    }
+
+    fn age_ms(&self) -> u64 {
+        timer::current_time().saturating_sub(self.created) / 1_000_000
+    }
+}
+
+/// Live objects by koid; always empty in release builds
+static LIVE: SpinMutex<BTreeMap<Koid, LiveObject>> = SpinMutex::new(BTreeMap::new());
+
+/// ============================================================================
+/// Tracked
+/// ============================================================================
+
+/// Membership of an object in the live object table
+///
+/// Put one in each object, made from the object's koid; dropping the
+/// object drops it from the table.
+#[cfg(debug_assertions)]
+pub struct Tracked {
+    koid: Koid,
+}
+
+#[cfg(debug_assertions)]
+impl Tracked {
+    /// Start tracking the object `koid`, created by the current process
+    pub fn new(koid: Koid, obj_type: ObjectType) -> Self {
+        let owner = crate::kernel::thread::get_current_thread()
+            .and_then(|t| t.pid())
+            .and_then(process::lookup)
+            .map_or(KOID_INVALID, |p| p.koid());
+        let object = LiveObject {
+            koid,
+            obj_type,
+            owner,
+            created: timer::current_time(),
+            created_by: Backtrace::capture(),
+        };
+        LIVE.lock().insert(koid, object);
+        Self { koid }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        LIVE.lock().remove(&self.koid);
+    }
+}
+
+/// Membership of an object in the live object table
+///
+/// Put one in each object, made from the object's koid; dropping the
+/// object drops it from the table.
+#[cfg(not(debug_assertions))]
+pub struct Tracked;
+
+#[cfg(not(debug_assertions))]
+impl Tracked {
+    /// Start tracking the object `koid`, created by the current process
+    #[inline]
+    pub fn new(_koid: Koid, _obj_type: ObjectType) -> Self {
+        Self
+    }
+}
+
+/// ============================================================================
+/// Queries
+/// ============================================================================
+
+/// Number of live tracked objects
+pub fn live_count() -> usize {
+    LIVE.lock().len()
+}
+
+/// Live objects created since `koid` was the next koid
+pub fn live_since(koid: Koid) -> Vec<LiveObject> {
+    LIVE.lock().range(koid..).map(|(_, o)| o.clone()).collect()
+}
+
+/// Live objects whose only handles were leaked by an exited process
+pub fn orphaned() -> Vec<LiveObject> {
+    // Copy out first; the process table isn't walked under the lock
+    let mut objects = live_since(koid::KOID_FIRST);
+    objects.retain(LiveObject::is_orphaned);
+    objects
+}
+
+/// Log every orphaned object, returning how many there were
+pub fn audit() -> usize {
+    LEAK_AUDITS.add(1);
+    let leaked = orphaned();
+    if !leaked.is_empty() {
+        LEAK_FOUND.add(leaked.len() as i64);
+        log_warn!("leak audit: {} objects outlived their process", leaked.len());
+        for object in &leaked {
+            object.print();
+        }
+    }
+    leaked.len()
+}
+
+/// ============================================================================
+/// Background Audit
+/// ============================================================================
+
+static AUDIT: Work = Work::new(audit_work);
+
+/// Background audit interval in nanoseconds
+static INTERVAL: AtomicU64 = AtomicU64::new(0);
+
+fn audit_work(work: &'static Work) {
+    audit();
+    workqueue::system().queue_delayed(work, INTERVAL.load(Ordering::Relaxed));
+}
+
+/// Start the background audit (needs the workqueue)
+pub fn init() {
+    let interval_ms = cmdline::cmdline_get_uint64(CMDLINE_INTERVAL, DEFAULT_INTERVAL_MS);
+    if !ENABLED || interval_ms == 0 {
+        return;
+    }
+    INTERVAL.store(interval_ms * 1_000_000, Ordering::Relaxed);
+    workqueue::system().queue_delayed(&AUDIT, interval_ms * 1_000_000);
+    log_info!("Leak audit every {} ms", interval_ms);
+}
+
+/// `leaks` console command
+fn cmd_leaks(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    if !ENABLED {
+        crate::println!("object tracking needs a debug build");
+        return 1;
+    }
+
+    if argc > 1 && argv[1] == "all" {
+        let objects = live_since(koid::KOID_FIRST);
+        crate::println!("{} live objects:", objects.len());
+        for object in &objects {
+            object.print();
+        }
+        return 0;
+    }
+
+    let leaked = audit();
+    crate::println!("{} live objects, {} orphaned", live_count(), leaked);
+    if leaked == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+crate::static_command!("leaks", "list objects leaked by exited processes", cmd_leaks);
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracked_lifetime() {
+        let first = koid::next_koid();
+        let koid = koid::alloc_koid();
+        let tracked = Tracked::new(koid, ObjectType::Event);
+
+        let live = live_since(first);
+        assert_eq!(live.len(), usize::from(ENABLED));
+        assert!(live.iter().all(|o| o.koid == koid && !o.is_orphaned()));
+
+        drop(tracked);
+        assert!(live_since(first).is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Message Packet Pool
+//!
+//! Every channel message queued in the kernel is charged against one
+//! global pool, and against the process and job that wrote it, until it
+//! is read or discarded. This bounds how much kernel memory queued IPC
+//! can pin, and stops a single process (or the processes of one job) from
+//! using up the pool for everyone else.
+//!
+//! # Design
+//!
+//! - **Charges**: [`PacketPool::charge`] returns a [`PacketCharge`] that
+//!   travels with the message and gives the bytes back when dropped, so a
+//!   message is released however it leaves the queue.
+//! - **Limits**: A charge that would take the pool, the process or the job
+//!   past its limit fails with `RX_ERR_NO_MEMORY`. Per-channel queue
+//!   quotas are separate and fail with `RX_ERR_SHOULD_WAIT` instead (see
+//!   [`channel`](super::channel)).
+//! - **Kernel owner**: Messages written by kernel threads are charged to
+//!   the pool only.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let charge = message_packet::charge(msg_bytes)?;
+//! let usage = message_packet::process_usage(process_koid);
+//! drop(charge);
+//! ```
+
+use crate::kernel::object::job::{JobId, JOB_ID_INVALID};
+use crate::kernel::object::koid::{Koid, KOID_INVALID};
+use crate::kernel::process;
+use crate::kernel::sync::Mutex;
+use crate::kernel::thread;
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use alloc::collections::BTreeMap;
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(PACKET_CHARGED, "channel.packet.charged");
+KCOUNTER!(PACKET_POOL_EXHAUSTED, "channel.packet.no_memory.pool");
+KCOUNTER!(PACKET_PROCESS_EXHAUSTED, "channel.packet.no_memory.process");
+KCOUNTER!(PACKET_JOB_EXHAUSTED, "channel.packet.no_memory.job");
+
+/// ============================================================================
+/// Limits
+/// ============================================================================
+
+/// Bytes of queued messages the whole system may hold
+pub const POOL_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Bytes of queued messages one process may have written
+pub const PROCESS_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Bytes of queued messages the processes of one job may have written
+pub const JOB_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// Byte limits of a [`PacketPool`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolLimits {
+    /// Limit for the whole pool
+    pub pool_bytes: usize,
+
+    /// Limit for each process
+    pub process_bytes: usize,
+
+    /// Limit for each job
+    pub job_bytes: usize,
+}
+
+impl PoolLimits {
+    /// The system pool's limits
+    pub const DEFAULT: Self = Self {
+        pool_bytes: POOL_MAX_BYTES,
+        process_bytes: PROCESS_MAX_BYTES,
+        job_bytes: JOB_MAX_BYTES,
+    };
+}
+
+/// ============================================================================
+/// Owners and Usage
+/// ============================================================================
+
+/// Who a queued message is charged to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketOwner {
+    /// Koid of the writing process, `KOID_INVALID` for the kernel
+    pub process: Koid,
+
+    /// Job of the writing process, `JOB_ID_INVALID` for the kernel
+    pub job: JobId,
+}
+
+impl PacketOwner {
+    /// Messages written by the kernel itself
+    pub const KERNEL: Self = Self {
+        process: KOID_INVALID,
+        job: JOB_ID_INVALID,
+    };
+
+    /// The process the current thread belongs to
+    ///
+    /// Kernel threads, and threads whose process has gone, are charged as
+    /// [`KERNEL`](Self::KERNEL).
+    pub fn current() -> Self {
+        thread::get_current_thread()
+            .and_then(|thread| thread.pid())
+            .and_then(process::lookup)
+            .map(|process| Self { process: process.koid(), job: process.job_id })
+            .unwrap_or(Self::KERNEL)
+    }
+
+    /// Whether this is the kernel
+    pub fn is_kernel(&self) -> bool {
+        self.process == KOID_INVALID
+    }
+}
+
+/// Queued bytes and messages charged to one owner, or to the whole pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketUsage {
+    /// Bytes charged
+    pub bytes: usize,
+
+    /// Messages charged
+    pub packets: usize,
+}
+
+impl PacketUsage {
+    fn add(&mut self, bytes: usize) {
+        self.bytes += bytes;
+        self.packets += 1;
+    }
+
+    fn sub(&mut self, bytes: usize) {
+        self.bytes -= bytes;
+        self.packets -= 1;
+    }
+}
+
+/// Remove `bytes` from `key`'s usage, forgetting owners with none left
+fn release_from<K: Ord>(map: &mut BTreeMap<K, PacketUsage>, key: K, bytes: usize) {
+    if let Some(usage) = map.get_mut(&key) {
+        usage.sub(bytes);
+        if usage.packets == 0 {
+            map.remove(&key);
+        }
+    }
+}
+
+/// ============================================================================
+/// Pool
+/// ============================================================================
+
+struct PoolState {
+    total: PacketUsage,
+    by_process: BTreeMap<Koid, PacketUsage>,
+    by_job: BTreeMap<JobId, PacketUsage>,
+}
+
+/// A budget of queued message bytes
+pub struct PacketPool {
+    limits: PoolLimits,
+    state: Mutex<PoolState>,
+}
+
+impl PacketPool {
+    /// Create an empty pool with `limits`
+    pub const fn new(limits: PoolLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::new(PoolState {
+                total: PacketUsage { bytes: 0, packets: 0 },
+                by_process: BTreeMap::new(),
+                by_job: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Charge one message of `bytes` to `owner`
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_NO_MEMORY` - the pool, the owner's process or its job
+    ///   would go over its limit
+    pub fn charge(&'static self, owner: PacketOwner, bytes: usize) -> Result<PacketCharge> {
+        let mut state = self.state.lock();
+
+        if state.total.bytes + bytes > self.limits.pool_bytes {
+            PACKET_POOL_EXHAUSTED.add(1);
+            return Err(RX_ERR_NO_MEMORY);
+        }
+        if !owner.is_kernel() {
+            let process = state.by_process.get(&owner.process).map_or(0, |u| u.bytes);
+            if process + bytes > self.limits.process_bytes {
+                PACKET_PROCESS_EXHAUSTED.add(1);
+                return Err(RX_ERR_NO_MEMORY);
+            }
+            let job = state.by_job.get(&owner.job).map_or(0, |u| u.bytes);
+            if job + bytes > self.limits.job_bytes {
+                PACKET_JOB_EXHAUSTED.add(1);
+                return Err(RX_ERR_NO_MEMORY);
+            }
+            state.by_process.entry(owner.process).or_default().add(bytes);
+            state.by_job.entry(owner.job).or_default().add(bytes);
+        }
+        state.total.add(bytes);
+        drop(state);
+
+        PACKET_CHARGED.add(1);
+        Ok(PacketCharge { pool: self, owner, bytes })
+    }
+
+    /// Give back a charge
+    fn release(&self, owner: PacketOwner, bytes: usize) {
+        let mut state = self.state.lock();
+        state.total.sub(bytes);
+        if !owner.is_kernel() {
+            release_from(&mut state.by_process, owner.process, bytes);
+            release_from(&mut state.by_job, owner.job, bytes);
+        }
+    }
+
+    /// Everything charged to the pool
+    pub fn usage(&self) -> PacketUsage {
+        self.state.lock().total
+    }
+
+    /// What process `koid` has charged
+    pub fn process_usage(&self, koid: Koid) -> PacketUsage {
+        self.state.lock().by_process.get(&koid).copied().unwrap_or_default()
+    }
+
+    /// What the processes of `job` have charged
+    pub fn job_usage(&self, job: JobId) -> PacketUsage {
+        self.state.lock().by_job.get(&job).copied().unwrap_or_default()
+    }
+
+    /// The pool's limits
+    pub fn limits(&self) -> PoolLimits {
+        self.limits
+    }
+}
+
+/// Bytes held in a [`PacketPool`] for one queued message
+///
+/// Released when dropped.
+pub struct PacketCharge {
+    pool: &'static PacketPool,
+    owner: PacketOwner,
+    bytes: usize,
+}
+
+impl PacketCharge {
+    /// Who the message is charged to
+    pub fn owner(&self) -> PacketOwner {
+        self.owner
+    }
+
+    /// Bytes charged
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for PacketCharge {
+    fn drop(&mut self) {
+        self.pool.release(self.owner, self.bytes);
+    }
+}
+
+/// ============================================================================
+/// System Pool
+/// ============================================================================
+
+/// The pool all channel messages are charged to
+pub static PACKET_POOL: PacketPool = PacketPool::new(PoolLimits::DEFAULT);
+
+/// Charge a message of `bytes` written by the current process to the
+/// system pool
+pub fn charge(bytes: usize) -> Result<PacketCharge> {
+    PACKET_POOL.charge(PacketOwner::current(), bytes)
+}
+
+/// Everything queued system-wide
+pub fn usage() -> PacketUsage {
+    PACKET_POOL.usage()
+}
+
+/// What process `koid` has queued
+pub fn process_usage(koid: Koid) -> PacketUsage {
+    PACKET_POOL.process_usage(koid)
+}
+
+/// What the processes of `job` have queued
+pub fn job_usage(job: JobId) -> PacketUsage {
+    PACKET_POOL.job_usage(job)
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: PoolLimits = PoolLimits {
+        pool_bytes: 1000,
+        process_bytes: 400,
+        job_bytes: 600,
+    };
+
+    fn owner(process: Koid, job: JobId) -> PacketOwner {
+        PacketOwner { process, job }
+    }
+
+    #[test]
+    fn test_charge_and_release() {
+        static POOL: PacketPool = PacketPool::new(LIMITS);
+
+        let a = POOL.charge(owner(2000, 5), 100).unwrap();
+        let b = POOL.charge(owner(2000, 5), 50).unwrap();
+        assert_eq!(POOL.usage(), PacketUsage { bytes: 150, packets: 2 });
+        assert_eq!(POOL.process_usage(2000), PacketUsage { bytes: 150, packets: 2 });
+        assert_eq!(POOL.job_usage(5).bytes, 150);
+
+        drop(a);
+        assert_eq!(POOL.process_usage(2000), PacketUsage { bytes: 50, packets: 1 });
+        drop(b);
+        assert_eq!(POOL.usage(), PacketUsage::default());
+        assert_eq!(POOL.job_usage(5), PacketUsage::default());
+    }
+
+    #[test]
+    fn test_process_and_job_limits() {
+        static POOL: PacketPool = PacketPool::new(LIMITS);
+
+        let _a = POOL.charge(owner(2000, 5), 400).unwrap();
+        assert_eq!(POOL.charge(owner(2000, 5), 1).err(), Some(RX_ERR_NO_MEMORY));
+
+        // Another process in the same job runs into the job's limit
+        let _b = POOL.charge(owner(2001, 5), 200).unwrap();
+        assert_eq!(POOL.charge(owner(2001, 5), 1).err(), Some(RX_ERR_NO_MEMORY));
+
+        // A process in another job only sees the pool's limit
+        let _c = POOL.charge(owner(2002, 6), 400).unwrap();
+        assert_eq!(POOL.charge(owner(2003, 7), 1).err(), Some(RX_ERR_NO_MEMORY));
+    }
+
+    #[test]
+    fn test_kernel_charges_pool_only() {
+        static POOL: PacketPool = PacketPool::new(LIMITS);
+
+        let _k = POOL.charge(PacketOwner::KERNEL, 900).unwrap();
+        assert_eq!(POOL.process_usage(KOID_INVALID), PacketUsage::default());
+        assert_eq!(POOL.charge(owner(2000, 5), 101).err(), Some(RX_ERR_NO_MEMORY));
+        assert!(POOL.charge(owner(2000, 5), 100).is_ok());
+    }
+}
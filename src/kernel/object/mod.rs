@@ -13,28 +13,51 @@
 //! # Design
 //!
 //! - **Capability-based security**: All operations through handles with rights
-//! - **Object types**: Process, Thread, VMO, VMAR, Channel, Event, Timer, Job, Port
+//! - **Object types**: Process, Thread, VMO, VMAR, Channel, Event, Counter, Timer, Job, Port
 //! - **Handle passing**: IPC can transfer handles with rights reduction
 //! - **Reference counting**: Automatic cleanup when last handle is closed
 //!
 //! # Modules
 //!
 //! - [`handle`] - Handle and rights model
+//! - [`koid`] - Kernel object IDs
+//! - [`leak`] - Live object tracking and leak audits (debug builds)
 //! - [`vmo`] - Virtual Memory Objects
 //! - [`channel`] - IPC channels
+//! - [`message_packet`] - Budget for queued channel messages
 //! - [`event`] - Event objects
+//! - [`counter`] - Counter objects
 //! - [`timer`] - Timer objects
+//! - [`interrupt`] - Interrupt objects for userspace drivers
+//! - [`iommu`] - IOMMU objects
+//! - [`bti`] - Bus transaction initiators for device DMA
+//! - [`pmt`] - Pinned memory tokens
+//! - [`clock`] - Clock objects and the system UTC clock
+//! - [`exception`] - Exception channels for userspace crash handlers
+//! - [`coredump`] - Core dumps of processes held at an exception
 
 
 pub mod handle;
+pub mod koid;
+pub mod leak;
 pub mod vmo;
 pub mod channel;
+pub mod message_packet;
 pub mod event;
+pub mod counter;
 pub mod timer;
 pub mod job;
+pub mod interrupt;
+pub mod iommu;
+pub mod bti;
+pub mod pmt;
+pub mod clock;
+pub mod exception;
+pub mod coredump;
 
 // Re-exports
 pub use handle::{
     Handle, HandleId, HandleOwner, HandleTable, KernelObjectBase, Rights, ObjectType,
 };
+pub use koid::{Koid, KOID_INVALID};
 pub use job::{Job, JobId, JobPolicy, ResourceLimits, JobStats};
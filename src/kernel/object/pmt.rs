@@ -0,0 +1,196 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Pinned Memory Tokens
+//!
+//! `rx_bti_pin` hands the driver a PMT for each pinned range. The pages
+//! stay pinned, and mapped for the device, for as long as the PMT lives;
+//! `rx_pmt_unpin` is the only clean way to end it.
+//!
+//! A PMT that goes away still pinned, because its handle was closed, is
+//! a driver bug: the device may still be doing DMA to the pages. The
+//! kernel logs a warning, counts it in `kernel.pmt.leaked` and moves the
+//! pin to its BTI's quarantine, where the pages stay until the driver
+//! calls `rx_bti_release_quarantine`.
+
+use crate::kernel::object::bti::{Bti, PinId};
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::vmo::Vmo;
+use crate::kernel::sync::spin::SpinMutex;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Result;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+// Import logging macros
+use crate::log_warn;
+
+KCOUNTER!(PMT_LEAKED, "kernel.pmt.leaked");
+
+/// PMT object identifier
+pub type PmtId = u64;
+
+/// Next PMT ID counter
+///
+/// PMT handle values are their IDs; starting high keeps them clear of the
+/// small values other registries hand out.
+static NEXT_PMT_ID: AtomicU64 = AtomicU64::new(5000);
+
+/// A pinned memory token
+pub struct Pmt {
+    /// Object ID
+    pub id: PmtId,
+
+    /// Kernel object ID
+    pub koid: Koid,
+
+    bti: Arc<Bti>,
+    pin: PinId,
+    vmo_koid: Koid,
+    offset: u64,
+    size: u64,
+
+    /// Still pinned
+    live: AtomicBool,
+}
+
+impl Pmt {
+    /// BTI the range is pinned through
+    pub fn bti(&self) -> &Arc<Bti> {
+        &self.bti
+    }
+
+    /// Whether the range is still pinned
+    pub fn is_pinned(&self) -> bool {
+        self.live.load(Ordering::Acquire)
+    }
+
+    /// Unpin the range
+    ///
+    /// Fails with `RX_ERR_BAD_STATE` if it already was.
+    pub fn unpin(&self) -> Result {
+        if !self.live.swap(false, Ordering::AcqRel) {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        match self.bti.unpin(self.pin) {
+            // Destroying the BTI already released the pin
+            Err(RX_ERR_NOT_FOUND) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl Drop for Pmt {
+    fn drop(&mut self) {
+        if !self.is_pinned() {
+            return;
+        }
+
+        PMT_LEAKED.add(1);
+        log_warn!(
+            "pmt {}: dropped without unpin (bti {} vmo koid {} offset {:#x} size {:#x}), quarantined",
+            self.id,
+            self.bti.id,
+            self.vmo_koid,
+            self.offset,
+            self.size
+        );
+        let _ = self.bti.quarantine(self.pin);
+    }
+}
+
+/// Live PMTs by ID
+static PMTS: SpinMutex<BTreeMap<PmtId, Arc<Pmt>>> = SpinMutex::new(BTreeMap::new());
+
+/// Pin `size` bytes of `vmo` at `offset` through `bti` and register a PMT
+/// for the pin
+///
+/// Returns the PMT and the device address of each page.
+pub fn pin(bti: &Arc<Bti>, vmo: Arc<Vmo>, offset: u64, size: u64, perms: u32) -> Result<(PmtId, Vec<u64>)> {
+    let vmo_koid = vmo.koid();
+    let (pin, addrs) = bti.pin(vmo, offset, size, perms)?;
+
+    let id = NEXT_PMT_ID.fetch_add(1, Ordering::Relaxed);
+    let pmt = Pmt {
+        id,
+        koid: alloc_koid(),
+        bti: bti.clone(),
+        pin,
+        vmo_koid,
+        offset,
+        size,
+        live: AtomicBool::new(true),
+    };
+    PMTS.lock().insert(id, Arc::new(pmt));
+    Ok((id, addrs))
+}
+
+/// Look up a PMT
+pub fn get(id: PmtId) -> Result<Arc<Pmt>> {
+    PMTS.lock().get(&id).cloned().ok_or(RX_ERR_NOT_FOUND)
+}
+
+/// Unpin a PMT's range and destroy it
+pub fn unpin(id: PmtId) -> Result {
+    let pmt = PMTS.lock().remove(&id).ok_or(RX_ERR_NOT_FOUND)?;
+    pmt.unpin()
+}
+
+/// Destroy a PMT without unpinning it
+///
+/// A range still pinned is quarantined with a warning. Returns false if
+/// there is no such PMT.
+pub fn close(id: PmtId) -> bool {
+    // Dropped outside the registry lock
+    let pmt = PMTS.lock().remove(&id);
+    pmt.is_some()
+}
+
+/// Number of live PMTs
+pub fn count() -> usize {
+    PMTS.lock().len()
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::dev::iommu::{perm, DummyIommu};
+    use crate::kernel::object::bti;
+
+    #[test]
+    fn test_pmt_unpin_and_leak() {
+        let bti = bti::get(bti::create(Arc::new(DummyIommu::new()), 0).unwrap()).unwrap();
+        let vmo = Arc::new(Vmo::create_physical(0x1000_0000, 0x2000).unwrap());
+
+        let (pmt, addrs) = pin(&bti, vmo.clone(), 0, 0x2000, perm::READ).unwrap();
+        assert_eq!(addrs, alloc::vec![0x1000_0000, 0x1000_1000]);
+        assert!(get(pmt).unwrap().is_pinned());
+        assert_eq!(unpin(pmt), Ok(()));
+        assert_eq!(unpin(pmt), Err(RX_ERR_NOT_FOUND));
+        assert_eq!(vmo.pinned_count(), 0);
+        assert_eq!(bti.quarantine_count(), 0);
+
+        // Closing a pinned PMT quarantines the pin
+        let (pmt, _) = pin(&bti, vmo.clone(), 0x1000, 0x1000, perm::WRITE).unwrap();
+        assert!(close(pmt));
+        assert!(!close(pmt));
+        assert_eq!(bti.quarantine_count(), 1);
+        assert_eq!(vmo.pinned_count(), 1);
+        assert_eq!(bti.release_quarantine(), 1);
+        assert_eq!(vmo.pinned_count(), 0);
+
+        bti::destroy(bti.id).unwrap();
+    }
+}
@@ -25,6 +25,9 @@
 //! ```
 
 
+use crate::kernel::object::handle::ObjectType;
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::leak::Tracked;
 use crate::kernel::sync::event::{Event, EventFlags};
 use crate::kernel::sync::Mutex;
 use crate::rustux::types::*;
@@ -146,6 +149,9 @@ pub struct Timer {
     /// Timer ID
     pub id: TimerId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Timer deadline (in nanoseconds)
     pub deadline: AtomicU64,
 
@@ -166,6 +172,9 @@ pub struct Timer {
 
     /// Reference count
     pub ref_count: AtomicUsize,
+
+    /// Entry in the live object table (debug builds)
+    tracked: Tracked,
 }
 
 impl Timer {
@@ -173,8 +182,10 @@ impl Timer {
     ///
     /// Initially disarmed.
     pub fn create() -> Result<Self> {
+        let koid = alloc_koid();
         Ok(Self {
             id: alloc_timer_id(),
+            koid,
             deadline: AtomicU64::new(0),
             slack: AtomicU64::new(0),
             period: Mutex::new(None),
@@ -182,6 +193,7 @@ impl Timer {
             event: Event::new(false, EventFlags::empty()),
             slack_policy: Mutex::new(SlackPolicy::Small),
             ref_count: AtomicUsize::new(1),
+            tracked: Tracked::new(koid, ObjectType::Timer),
         })
     }
 
@@ -190,6 +202,11 @@ impl Timer {
         self.id
     }
 
+    /// Get the kernel object ID
+    pub const fn koid(&self) -> Koid {
+        self.koid
+    }
+
     /// Get timer state
     pub fn state(&self) -> TimerState {
         TimerState::from_raw(self.state.load(Ordering::Acquire))
@@ -15,6 +15,51 @@
 //! - **COW clones**: Copy-on-write for efficient memory sharing
 //! - **Resizable**: VMOs can grow/shrink if created with RESIZABLE flag
 //! - **Cache policy**: Control cache behavior (uncached, write-combining, etc.)
+//! - **Range operations**: Commit, decommit, zero and cache maintenance over
+//!   a byte range (`op_range`)
+//! - **Discardable**: Unlocked DISCARDABLE VMOs may lose their pages under
+//!   memory pressure
+//! - **Page transfer**: Move committed pages between VMOs without copying
+//!   (`transfer_data`)
+//!
+//! # Mapping Fixups
+//!
+//! Mappings made with `Vmar::map_to_aspace` are recorded on the VMO. When
+//! pages go away, by a shrinking resize or `VmoOp::Decommit`, their page
+//! table entries are removed from every such mapping first, so the next
+//! access faults instead of reaching a freed page.
+//!
+//! # Discardable VMOs
+//!
+//! A cache can create its VMO DISCARDABLE and lock it with `VmoOp::Lock`
+//! while using the contents. When memory runs low the kernel frees the
+//! pages of every unlocked discardable VMO (`Vmo::discard`); the next lock
+//! reports that the contents are gone and must be rebuilt.
+//!
+//! `VmoOp::TryLock` locks only if the contents survived, so a cache can
+//! drop an entry instead of rebuilding it. Reclaim also asserts the
+//! VMO's `VMO_DISCARDED` signal for caches that want to know right away.
+//!
+//! # Pinning
+//!
+//! Pages pinned for DMA ([`Vmo::pin`]) stay committed at the same
+//! physical address until unpinned. Operations that would free or move
+//! them, a shrinking resize, `VmoOp::Decommit` or `transfer_data` over
+//! the range, fail with `RX_ERR_BAD_STATE`, and reclaim skips a
+//! discardable VMO with pinned pages.
+//!
+//! # Page Aging
+//!
+//! [`Vmo::harvest`] scans the accessed/dirty bits of every mapping of a
+//! range and folds them into the PMM's per-page ages, which eviction
+//! policy can rank pages by.
+//!
+//! # NUMA Placement
+//!
+//! Each VMO has a [`NumaPolicy`] choosing the node its pages are
+//! committed on: the committing CPU's node, or round-robin across nodes by
+//! page index. The PMM falls back to other nodes when the chosen one is
+//! out of memory.
 //!
 //! # Usage
 //!
@@ -25,11 +70,18 @@
 //! ```
 
 
+use crate::kernel::numa::{NumaPolicy, NUMA_NODE_ANY};
+use crate::kernel::object::handle::ObjectType;
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::object::leak::Tracked;
 use crate::kernel::pmm;
 use crate::kernel::sync::Mutex;
+use crate::kernel::vm::aspace::AddressSpace;
+use crate::kernel::vm::page_table::PageUsage;
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
@@ -67,6 +119,15 @@ impl VmoFlags {
     /// VMO is a COW clone
     pub const COW: Self = Self(0x02);
 
+    /// VMO wraps a fixed physical range (device memory)
+    pub const PHYSICAL: Self = Self(0x04);
+
+    /// VMO is backed by physically contiguous pages (DMA buffers)
+    pub const CONTIGUOUS: Self = Self(0x08);
+
+    /// VMO's pages may be reclaimed while it is unlocked
+    pub const DISCARDABLE: Self = Self(0x10);
+
     /// Check if resizable
     pub const fn is_resizable(self) -> bool {
         (self.0 & Self::RESIZABLE.0) != 0
@@ -77,6 +138,21 @@ impl VmoFlags {
         (self.0 & Self::COW.0) != 0
     }
 
+    /// Check if backed by a fixed physical range
+    pub const fn is_physical(self) -> bool {
+        (self.0 & Self::PHYSICAL.0) != 0
+    }
+
+    /// Check if physically contiguous
+    pub const fn is_contiguous(self) -> bool {
+        (self.0 & Self::CONTIGUOUS.0) != 0
+    }
+
+    /// Check if discardable
+    pub const fn is_discardable(self) -> bool {
+        (self.0 & Self::DISCARDABLE.0) != 0
+    }
+
     /// Create from raw value
     pub const fn from_raw(raw: u32) -> Self {
         Self(raw)
@@ -128,12 +204,105 @@ impl CachePolicy {
         }
     }
 
+    /// Create from a raw value passed by userspace
+    ///
+    /// Unlike `from_raw`, unknown values are rejected.
+    pub const fn try_from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Default),
+            1 => Some(Self::Uncached),
+            2 => Some(Self::WriteCombining),
+            3 => Some(Self::WriteThrough),
+            _ => None,
+        }
+    }
+
     /// Get raw value
     pub const fn into_raw(self) -> u32 {
         self as u32
     }
 }
 
+/// ============================================================================
+/// Range Operations
+/// ============================================================================
+
+/// Operations for `Vmo::op_range`
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmoOp {
+    /// Commit every page in the range
+    Commit = 1,
+
+    /// Free the committed pages in the range (page-aligned range only)
+    Decommit = 2,
+
+    /// Invalidate the data cache over the range
+    CacheInvalidate = 7,
+
+    /// Write dirty cache lines in the range back to memory
+    CacheClean = 8,
+
+    /// Clean, then invalidate
+    CacheCleanInvalidate = 9,
+
+    /// Zero the range, leaving uncommitted pages uncommitted
+    Zero = 10,
+
+    /// Keep a discardable VMO's pages (whole VMO only)
+    Lock = 20,
+
+    /// `Lock`, but only if the pages weren't discarded (whole VMO only)
+    TryLock = 21,
+
+    /// Let a discardable VMO's pages be reclaimed (whole VMO only)
+    Unlock = 22,
+}
+
+impl VmoOp {
+    /// Create from raw value, rejecting unknown operations
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Commit),
+            2 => Some(Self::Decommit),
+            7 => Some(Self::CacheInvalidate),
+            8 => Some(Self::CacheClean),
+            9 => Some(Self::CacheCleanInvalidate),
+            10 => Some(Self::Zero),
+            20 => Some(Self::Lock),
+            21 => Some(Self::TryLock),
+            22 => Some(Self::Unlock),
+            _ => None,
+        }
+    }
+
+    /// Whether this is `Lock`, `TryLock` or `Unlock`
+    pub const fn is_lock_op(self) -> bool {
+        matches!(self, Self::Lock | Self::TryLock | Self::Unlock)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+type Arch = crate::kernel::arch::amd64::Amd64Arch;
+
+#[cfg(target_arch = "aarch64")]
+type Arch = crate::kernel::arch::arm64::Arm64Arch;
+
+#[cfg(target_arch = "riscv64")]
+type Arch = crate::kernel::arch::riscv64::Riscv64Arch;
+
+/// Run a data cache operation over kernel addresses `[addr, addr + len)`
+unsafe fn cache_maintenance(op: VmoOp, addr: VAddr, len: usize) {
+    use crate::kernel::arch::arch_traits::ArchCache;
+
+    match op {
+        VmoOp::CacheInvalidate => <Arch as ArchCache>::invalidate_dcache(addr, len),
+        VmoOp::CacheClean => <Arch as ArchCache>::clean_dcache(addr, len),
+        VmoOp::CacheCleanInvalidate => <Arch as ArchCache>::clean_invalidate_dcache(addr, len),
+        _ => {}
+    }
+}
+
 /// ============================================================================
 /// Page Map
 /// ============================================================================
@@ -159,7 +328,7 @@ pub struct PageMap {
     pages: Mutex<BTreeMap<usize, PageMapEntry>>,
 
     /// Total number of pages
-    total_pages: usize,
+    total_pages: AtomicUsize,
 
     /// Number of committed pages
     committed_pages: AtomicUsize,
@@ -170,7 +339,7 @@ impl PageMap {
     pub const fn new(total_pages: usize) -> Self {
         Self {
             pages: Mutex::new(BTreeMap::new()),
-            total_pages,
+            total_pages: AtomicUsize::new(total_pages),
             committed_pages: AtomicUsize::new(0),
         }
     }
@@ -185,6 +354,11 @@ impl PageMap {
 
     /// Allocate and commit a page at offset
     pub fn allocate(&self, offset: usize) -> Result<PAddr> {
+        self.allocate_on(offset, NUMA_NODE_ANY)
+    }
+
+    /// Allocate and commit a page at offset, preferably on NUMA `node`
+    pub fn allocate_on(&self, offset: usize, node: u32) -> Result<PAddr> {
         // Check if page already exists
         {
             let mut pages = self.pages.lock();
@@ -195,8 +369,8 @@ impl PageMap {
             }
         }
 
-        // Allocate new page
-        let paddr = pmm::alloc_page()?;
+        // Allocate new page; VMO pages always start out zeroed
+        let paddr = pmm::pmm_alloc_zeroed_page_on_node(node, pmm::PMM_ALLOC_FLAG_ANY)?;
         let vaddr = pmm::paddr_to_vaddr(paddr) as PAddr;
 
         // Add to map
@@ -211,6 +385,49 @@ impl PageMap {
         Ok(vaddr)
     }
 
+    /// Commit an existing physical page at offset
+    ///
+    /// Used for physical VMOs, whose pages are not owned by the PMM.
+    pub fn insert(&self, offset: usize, paddr: PAddr) {
+        let vaddr = crate::kernel::mmu::phys_to_virt(paddr) as PAddr;
+        let mut pages = self.pages.lock();
+        if pages.insert(offset, PageMapEntry {
+            paddr: vaddr,
+            present: true,
+            writable: true,
+        }).is_none() {
+            self.committed_pages.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Commit a page taken from another VMO's map with `remove`
+    fn put(&self, offset: usize, vaddr: PAddr) {
+        let mut pages = self.pages.lock();
+        if pages.insert(offset, PageMapEntry {
+            paddr: vaddr,
+            present: true,
+            writable: true,
+        }).is_none() {
+            self.committed_pages.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Remove the page at offset, returning its address if it was committed
+    pub fn remove(&self, offset: usize) -> Option<PAddr> {
+        let entry = self.pages.lock().remove(&offset)?;
+        self.committed_pages.fetch_sub(1, Ordering::Relaxed);
+        Some(entry.paddr)
+    }
+
+    /// Committed pages in `[first, last)`, as (page offset, address) pairs
+    pub fn committed_in(&self, first: usize, last: usize) -> Vec<(usize, PAddr)> {
+        self.pages
+            .lock()
+            .range(first..last)
+            .map(|(&offset, entry)| (offset, entry.paddr))
+            .collect()
+    }
+
     /// Mark a page as copy-on-write
     pub fn mark_cow(&self, offset: usize) {
         let mut pages = self.pages.lock();
@@ -225,11 +442,49 @@ impl PageMap {
     }
 
     /// Get total page count
-    pub const fn total_count(&self) -> usize {
-        self.total_pages
+    pub fn total_count(&self) -> usize {
+        self.total_pages.load(Ordering::Relaxed)
+    }
+
+    /// Set total page count after a resize
+    pub fn set_total_count(&self, total_pages: usize) {
+        self.total_pages.store(total_pages, Ordering::Relaxed);
     }
 }
 
+/// ============================================================================
+/// VMO Mapping
+/// ============================================================================
+
+/// A range of a VMO mapped into an address space
+struct VmoMapping {
+    /// Address space holding the mapping
+    aspace: Arc<AddressSpace>,
+
+    /// Virtual address of the first mapped page
+    vaddr: VAddr,
+
+    /// Offset of the mapping within the VMO
+    vmo_offset: usize,
+
+    /// Size of the mapping in bytes
+    size: usize,
+}
+
+/// ============================================================================
+/// Discard State
+/// ============================================================================
+
+/// Lock state of a discardable VMO
+#[derive(Debug, Default)]
+struct DiscardState {
+    /// Outstanding `VmoOp::Lock`s
+    locks: usize,
+
+    /// Whether the pages were discarded since the last lock
+    discarded: bool,
+}
+
 /// ============================================================================
 /// VMO Parent
 /// ============================================================================
@@ -258,6 +513,9 @@ pub struct Vmo {
     /// VMO ID
     pub id: VmoId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// VMO size in bytes
     pub size: AtomicU64,
 
@@ -276,12 +534,30 @@ pub struct Vmo {
     /// Cache policy
     pub cache_policy: Mutex<CachePolicy>,
 
+    /// NUMA placement of committed pages
+    numa_policy: Mutex<NumaPolicy>,
+
     /// Reference count
     pub ref_count: AtomicUsize,
 
     /// Address spaces this VMO is mapped into (for shared memory tracking)
     /// Stores AddressSpace IDs (or Process IDs)
     mapped_aspaces: Mutex<alloc::collections::BTreeSet<u64>>,
+
+    /// Number of VMAR mappings of this VMO
+    mapping_count: AtomicUsize,
+
+    /// Mappings with page table entries, fixed up when pages go away
+    aspace_mappings: Mutex<Vec<VmoMapping>>,
+
+    /// Lock state, for DISCARDABLE VMOs
+    discard: Mutex<DiscardState>,
+
+    /// Pin counts of pinned pages, by page index
+    pins: Mutex<BTreeMap<usize, usize>>,
+
+    /// Entry in the live object table (debug builds)
+    tracked: Tracked,
 }
 
 impl Vmo {
@@ -303,32 +579,113 @@ impl Vmo {
         }
 
         let page_count = size / 4096;
+        let koid = alloc_koid();
 
         Ok(Self {
             id: alloc_vmo_id(),
+            koid,
             size: AtomicU64::new(size as u64),
             flags,
             pages: PageMap::new(page_count),
             parent: Mutex::new(None),
             children: Mutex::new(Vec::new()),
             cache_policy: Mutex::new(CachePolicy::Default),
+            numa_policy: Mutex::new(NumaPolicy::default()),
             ref_count: AtomicUsize::new(1),
             mapped_aspaces: Mutex::new(alloc::collections::BTreeSet::new()),
+            mapping_count: AtomicUsize::new(0),
+            aspace_mappings: Mutex::new(Vec::new()),
+            discard: Mutex::new(DiscardState::default()),
+            pins: Mutex::new(BTreeMap::new()),
+            tracked: Tracked::new(koid, ObjectType::Vmo),
         })
     }
 
+    /// Create a VMO over a fixed physical range
+    ///
+    /// Used to hand device memory (e.g. PCI BARs) to drivers. All pages
+    /// are committed up front and the VMO defaults to uncached.
+    ///
+    /// # Arguments
+    ///
+    /// * `paddr` - Physical base address (must be page-aligned)
+    /// * `size` - Size in bytes (must be page-aligned)
+    pub fn create_physical(paddr: PAddr, size: usize) -> Result<Self> {
+        if (paddr & 0xFFF) != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        let vmo = Self::create(size, VmoFlags::PHYSICAL)?;
+        for page in 0..size / 4096 {
+            vmo.pages.insert(page, paddr + (page * 4096) as PAddr);
+        }
+        vmo.set_cache_policy(CachePolicy::Uncached)?;
+        Ok(vmo)
+    }
+
+    /// Create a VMO backed by physically contiguous pages
+    ///
+    /// Used for DMA buffers that a device addresses as one range.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Size in bytes (must be page-aligned)
+    /// * `align_log2` - Log2 of the physical alignment
+    pub fn create_contiguous(size: usize, align_log2: u8) -> Result<Self> {
+        if size == 0 || (size & 0xFFF) != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        let page_count = size / 4096;
+        let paddr = pmm::pmm_alloc_contiguous(page_count, 0, align_log2)?;
+
+        let vmo = Self::create(size, VmoFlags::CONTIGUOUS)?;
+        for page in 0..page_count {
+            vmo.pages.insert(page, paddr + (page * 4096) as PAddr);
+        }
+        Ok(vmo)
+    }
+
+    /// Commit the page at `index` and return its physical address
+    ///
+    /// Used when pinning pages for DMA and when faulting in guest and user
+    /// memory. Physical and contiguous VMOs already hold all their pages.
+    pub fn commit_page_paddr(&self, index: usize) -> Result<PAddr> {
+        if index >= self.size() / 4096 {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        if !self.owns_pages() {
+            return self.pages.get(index).ok_or(RX_ERR_NOT_FOUND);
+        }
+        let vaddr = self.commit(index)?;
+        crate::kernel::mmu::virt_to_phys(vaddr as VAddr).ok_or(RX_ERR_INTERNAL)
+    }
+
+    /// Get the kernel object ID
+    pub const fn koid(&self) -> Koid {
+        self.koid
+    }
+
     /// Get size
     pub fn size(&self) -> usize {
         self.size.load(Ordering::Acquire) as usize
     }
 
+    /// Whether the VMO's pages come from the PMM and may be freed
+    fn owns_pages(&self) -> bool {
+        !self.flags.is_physical() && !self.flags.is_contiguous()
+    }
+
     /// Resize the VMO
     ///
+    /// Shrinking unmaps the pages past the new end from every mapping and
+    /// frees them; growing adds uncommitted pages.
+    ///
     /// # Arguments
     ///
     /// * `new_size` - New size in bytes (must be page-aligned)
     pub fn resize(&self, new_size: usize) -> Result {
-        if !self.flags.is_resizable() {
+        if !self.flags.is_resizable() || !self.owns_pages() {
             return Err(RX_ERR_NOT_SUPPORTED);
         }
 
@@ -336,10 +693,384 @@ impl Vmo {
             return Err(RX_ERR_INVALID_ARGS);
         }
 
-        self.size.store(new_size as u64, Ordering::Release);
+        if new_size < self.size() && self.is_pinned(new_size / 4096, usize::MAX) {
+            return Err(RX_ERR_BAD_STATE);
+        }
+
+        let old_size = self.size.swap(new_size as u64, Ordering::AcqRel) as usize;
+        self.pages.set_total_count(new_size / 4096);
+
+        if new_size < old_size {
+            self.unmap_pages(new_size / 4096, old_size / 4096);
+            self.free_pages(new_size / 4096, old_size / 4096);
+        }
         Ok(())
     }
 
+    /// Run a range operation over `[offset, offset + len)`
+    ///
+    /// Ranges that aren't page-aligned cover every page they touch, except
+    /// that `Decommit` must be page-aligned. Cache operations and `Zero`
+    /// only touch committed pages; uncommitted pages already read as zero.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_OUT_OF_RANGE` - the range extends past the end of the VMO
+    /// - `RX_ERR_INVALID_ARGS` - `Decommit` of an unaligned range, or a lock
+    ///   operation on less than the whole VMO
+    /// - `RX_ERR_NOT_SUPPORTED` - `Decommit` of a physical or contiguous VMO,
+    ///   or a lock operation on a VMO that isn't discardable
+    /// - `RX_ERR_NOT_FOUND` - `TryLock` of a VMO whose pages were discarded
+    /// - `RX_ERR_BAD_STATE` - `Unlock` of a VMO that isn't locked, or
+    ///   `Decommit` of pinned pages
+    /// - `RX_ERR_NO_MEMORY` - `Commit` ran out of pages; the pages committed
+    ///   before that stay committed
+    pub fn op_range(&self, op: VmoOp, offset: usize, len: usize) -> Result {
+        if op.is_lock_op() {
+            if offset != 0 || len != self.size() {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+            return match op {
+                VmoOp::Lock => self.lock().map(|_| ()),
+                VmoOp::TryLock => self.try_lock(),
+                _ => self.unlock(),
+            };
+        }
+
+        let end = offset.checked_add(len).ok_or(RX_ERR_OUT_OF_RANGE)?;
+        if end > self.size() {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        if len == 0 {
+            return Ok(());
+        }
+
+        let first = offset / 4096;
+        let last = (end + 4095) / 4096;
+
+        match op {
+            VmoOp::Commit => {
+                // Physical and contiguous VMOs are committed when created
+                if self.owns_pages() {
+                    for page in first..last {
+                        self.commit(page)?;
+                    }
+                }
+            }
+            VmoOp::Decommit => {
+                if !self.owns_pages() {
+                    return Err(RX_ERR_NOT_SUPPORTED);
+                }
+                if (offset | len) & 0xFFF != 0 {
+                    return Err(RX_ERR_INVALID_ARGS);
+                }
+                if self.is_pinned(first, last) {
+                    return Err(RX_ERR_BAD_STATE);
+                }
+                self.unmap_pages(first, last);
+                self.free_pages(first, last);
+            }
+            VmoOp::Lock | VmoOp::TryLock | VmoOp::Unlock => {}
+            VmoOp::Zero | VmoOp::CacheInvalidate | VmoOp::CacheClean | VmoOp::CacheCleanInvalidate => {
+                for (page, vaddr) in self.pages.committed_in(first, last) {
+                    // Clip the range to this page
+                    let start = offset.max(page * 4096) - page * 4096;
+                    let stop = end.min((page + 1) * 4096) - page * 4096;
+                    let addr = vaddr as VAddr + start;
+
+                    unsafe {
+                        if op == VmoOp::Zero {
+                            core::ptr::write_bytes(addr as *mut u8, 0, stop - start);
+                        } else {
+                            cache_maintenance(op, addr, stop - start);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lock a discardable VMO so its pages aren't discarded
+    ///
+    /// Locks nest. Returns whether the pages were discarded since the VMO
+    /// was last locked, in which case it now reads as zero.
+    pub fn lock(&self) -> Result<bool> {
+        if !self.flags.is_discardable() {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        let mut state = self.discard.lock();
+        state.locks += 1;
+        Ok(core::mem::replace(&mut state.discarded, false))
+    }
+
+    /// Lock a discardable VMO only if its pages weren't discarded
+    ///
+    /// Fails with `RX_ERR_NOT_FOUND`, leaving the VMO unlocked, if they
+    /// were; a later `lock` still reports the discard.
+    pub fn try_lock(&self) -> Result {
+        if !self.flags.is_discardable() {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        let mut state = self.discard.lock();
+        if state.discarded {
+            return Err(RX_ERR_NOT_FOUND);
+        }
+        state.locks += 1;
+        Ok(())
+    }
+
+    /// Drop a lock taken by `lock` or `try_lock`
+    pub fn unlock(&self) -> Result {
+        if !self.flags.is_discardable() {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+
+        let mut state = self.discard.lock();
+        if state.locks == 0 {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        state.locks -= 1;
+        Ok(())
+    }
+
+    /// Free every page of an unlocked discardable VMO
+    ///
+    /// Called by memory reclaim. Returns the number of pages freed; a VMO
+    /// that isn't discardable, is locked or has nothing committed gives 0.
+    pub fn discard(&self) -> usize {
+        if !self.flags.is_discardable() {
+            return 0;
+        }
+
+        let mut state = self.discard.lock();
+        let committed = self.pages.committed_count();
+        if state.locks != 0 || committed == 0 || !self.pins.lock().is_empty() {
+            return 0;
+        }
+
+        let pages = self.size() / 4096;
+        self.unmap_pages(0, pages);
+        self.free_pages(0, pages);
+        state.discarded = true;
+        committed
+    }
+
+    /// Move the pages of `src` at `[src_offset, src_offset + len)` to
+    /// `[offset, offset + len)` of this VMO without copying
+    ///
+    /// Both ranges are unmapped first, this VMO's pages in the range are
+    /// freed, then each committed source page moves over; uncommitted
+    /// source pages leave uncommitted destination pages. The source range
+    /// reads as zero afterwards.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_INVALID_ARGS` - an offset or `len` isn't page-aligned, or
+    ///   the ranges overlap in the same VMO
+    /// - `RX_ERR_OUT_OF_RANGE` - a range extends past the end of its VMO
+    /// - `RX_ERR_NOT_SUPPORTED` - either VMO is physical or contiguous
+    /// - `RX_ERR_BAD_STATE` - either VMO is or has a clone, which may
+    ///   share the pages, or either range has pinned pages
+    pub fn transfer_data(&self, offset: usize, len: usize, src: &Vmo, src_offset: usize) -> Result {
+        if (offset | len | src_offset) & 0xFFF != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let end = offset.checked_add(len).ok_or(RX_ERR_OUT_OF_RANGE)?;
+        let src_end = src_offset.checked_add(len).ok_or(RX_ERR_OUT_OF_RANGE)?;
+        if end > self.size() || src_end > src.size() {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        if core::ptr::eq(self, src) && offset < src_end && src_offset < end {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        if !self.owns_pages() || !src.owns_pages() {
+            return Err(RX_ERR_NOT_SUPPORTED);
+        }
+        for vmo in [self, src] {
+            if !vmo.children.lock().is_empty() || vmo.parent.lock().is_some() {
+                return Err(RX_ERR_BAD_STATE);
+            }
+        }
+        if len == 0 {
+            return Ok(());
+        }
+
+        let (first, last) = (offset / 4096, end / 4096);
+        let (src_first, src_last) = (src_offset / 4096, src_end / 4096);
+        if self.is_pinned(first, last) || src.is_pinned(src_first, src_last) {
+            return Err(RX_ERR_BAD_STATE);
+        }
+
+        src.unmap_pages(src_first, src_last);
+        self.unmap_pages(first, last);
+        self.free_pages(first, last);
+
+        for (page, _) in src.pages.committed_in(src_first, src_last) {
+            if let Some(vaddr) = src.pages.remove(page) {
+                self.pages.put(first + (page - src_first), vaddr);
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit and pin the pages of `[offset, offset + len)`
+    ///
+    /// Pins nest; each must be dropped with a matching `unpin`. On error
+    /// nothing stays pinned.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_INVALID_ARGS` - the range is empty or not page-aligned
+    /// - `RX_ERR_OUT_OF_RANGE` - the range extends past the end of the VMO
+    /// - `RX_ERR_NO_MEMORY` - a page couldn't be committed
+    pub fn pin(&self, offset: usize, len: usize) -> Result {
+        if len == 0 || (offset | len) & 0xFFF != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let end = offset.checked_add(len).ok_or(RX_ERR_OUT_OF_RANGE)?;
+        if end > self.size() {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        let (first, last) = (offset / 4096, end / 4096);
+        for page in first..last {
+            self.commit(page)?;
+        }
+
+        let mut pins = self.pins.lock();
+        for page in first..last {
+            *pins.entry(page).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    /// Drop a pin taken by `pin` on the same range
+    pub fn unpin(&self, offset: usize, len: usize) -> Result {
+        if (offset | len) & 0xFFF != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let (first, last) = (offset / 4096, (offset + len) / 4096);
+
+        let mut pins = self.pins.lock();
+        if (first..last).any(|page| !pins.contains_key(&page)) {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        for page in first..last {
+            if let Some(count) = pins.get_mut(&page) {
+                *count -= 1;
+                if *count == 0 {
+                    pins.remove(&page);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any page in `[first, last)` is pinned
+    pub fn is_pinned(&self, first: usize, last: usize) -> bool {
+        self.pins.lock().range(first..last).next().is_some()
+    }
+
+    /// Number of pinned pages
+    pub fn pinned_count(&self) -> usize {
+        self.pins.lock().len()
+    }
+
+    /// Free the committed pages in `[first, last)`
+    fn free_pages(&self, first: usize, last: usize) {
+        for (page, _) in self.pages.committed_in(first, last) {
+            if let Some(vaddr) = self.pages.remove(page) {
+                if let Some(paddr) = crate::kernel::mmu::virt_to_phys(vaddr as VAddr) {
+                    pmm::free_page(paddr);
+                }
+            }
+        }
+    }
+
+    /// Remove pages `[first, last)` from every recorded mapping
+    fn unmap_pages(&self, first: usize, last: usize) {
+        let (start, end) = (first * 4096, last * 4096);
+
+        for mapping in self.aspace_mappings.lock().iter() {
+            let lo = start.max(mapping.vmo_offset);
+            let hi = end.min(mapping.vmo_offset + mapping.size);
+            if lo >= hi {
+                continue;
+            }
+
+            // Uncommitted pages were never mapped, so NotMapped is expected
+            let vaddr = mapping.vaddr + (lo - mapping.vmo_offset);
+            let _ = mapping.aspace.unmap(vaddr, (hi - lo) / 4096);
+            mapping.aspace.flush_tlb();
+        }
+    }
+
+    /// Record a mapping of `[vmo_offset, vmo_offset + size)` at `vaddr`
+    ///
+    /// Called by `Vmar::map_to_aspace` once the pages are in the page table.
+    pub fn add_aspace_mapping(&self, aspace: Arc<AddressSpace>, vaddr: VAddr, vmo_offset: usize, size: usize) {
+        self.aspace_mappings.lock().push(VmoMapping {
+            aspace,
+            vaddr,
+            vmo_offset,
+            size,
+        });
+    }
+
+    /// Harvest the accessed/dirty bits of `[offset, offset + len)`
+    ///
+    /// Reads and clears the bits in every mapping of the range, then ages
+    /// each committed page in the PMM: a page any mapping accessed goes
+    /// back to age 0, the others get one harvest older. Returns the
+    /// (offset, age) of each committed page, in offset order.
+    ///
+    /// Only accesses through mappings count; `read` and `write` don't set
+    /// the bits. Pages the PMM doesn't manage, as in physical VMOs, are
+    /// left out.
+    pub fn harvest(&self, offset: usize, len: usize) -> Result<Vec<(usize, pmm::PageAge)>> {
+        let end = offset.checked_add(len).ok_or(RX_ERR_OUT_OF_RANGE)?;
+        let (first, last) = (offset / 4096, (end + 4095) / 4096);
+
+        // A page mapped more than once is used if any mapping used it
+        let mut usage: BTreeMap<usize, PageUsage> = BTreeMap::new();
+        for mapping in self.aspace_mappings.lock().iter() {
+            let lo = (first * 4096).max(mapping.vmo_offset);
+            let hi = (last * 4096).min(mapping.vmo_offset + mapping.size);
+            if lo >= hi {
+                continue;
+            }
+
+            let vaddr = mapping.vaddr + (lo - mapping.vmo_offset);
+            let _ = mapping.aspace.harvest(vaddr, (hi - lo) / 4096, true, |paddr, seen| {
+                let page = usage.entry(paddr).or_default();
+                page.accessed |= seen.accessed;
+                page.dirty |= seen.dirty;
+            });
+        }
+
+        Ok(self
+            .pages
+            .committed_in(first, last)
+            .into_iter()
+            .filter_map(|(page, vaddr)| {
+                let paddr = crate::kernel::mmu::virt_to_phys(vaddr as VAddr)?;
+                let seen = usage.get(&(paddr as usize)).copied().unwrap_or_default();
+                pmm::pmm_page_harvested(paddr, seen.accessed, seen.dirty);
+                pmm::pmm_page_age(paddr).map(|age| (page * 4096, age))
+            })
+            .collect())
+    }
+
+    /// Forget the mapping at `vaddr` in `aspace`
+    pub fn remove_aspace_mapping(&self, aspace: &AddressSpace, vaddr: VAddr) {
+        self.aspace_mappings
+            .lock()
+            .retain(|m| !(core::ptr::eq(Arc::as_ptr(&m.aspace), aspace) && m.vaddr == vaddr));
+    }
+
     /// Read from VMO
     ///
     /// # Arguments
@@ -367,7 +1098,7 @@ impl Vmo {
             let offset_in_page = current_offset & 0xFFF;
 
             // Get or allocate page
-            let paddr = self.pages.allocate(page_offset / 4096)?;
+            let paddr = self.commit(page_offset / 4096)?;
 
             // Copy data from page
             let src = unsafe { (paddr as *const u8).add(offset_in_page) };
@@ -416,7 +1147,7 @@ impl Vmo {
             let offset_in_page = current_offset & 0xFFF;
 
             // Get or allocate page
-            let paddr = self.pages.allocate(page_offset / 4096)?;
+            let paddr = self.commit(page_offset / 4096)?;
 
             // Copy data to page
             let dst = unsafe { (paddr as *mut u8).add(offset_in_page) };
@@ -438,9 +1169,45 @@ impl Vmo {
         Ok(len)
     }
 
+    /// Make writes to `[offset, offset + len)` visible to instruction fetch
+    ///
+    /// For code changed through the VMO rather than through a mapping, as
+    /// when a debugger sets a breakpoint. Pages not committed are skipped.
+    pub fn sync_icache(&self, offset: usize, len: usize) {
+        use crate::kernel::arch::arch_traits::ArchCache;
+
+        let end = offset + len;
+        for (page, vaddr) in self.pages.committed_in(offset / 4096, end.div_ceil(4096)) {
+            let start = offset.max(page * 4096) - page * 4096;
+            let stop = end.min((page + 1) * 4096) - page * 4096;
+            unsafe { <Arch as ArchCache>::sync_icache(vaddr as VAddr + start, stop - start) };
+        }
+    }
+
     /// Set cache policy
-    pub fn set_cache_policy(&self, policy: CachePolicy) {
+    ///
+    /// The policy applies to every later mapping of the VMO. It can only
+    /// change while no existing mapping, clone or committed page could
+    /// still be using the old one. Physical VMOs may change it whenever
+    /// they are unmapped, since nothing else maps their pages.
+    ///
+    /// # Errors
+    ///
+    /// - `RX_ERR_BAD_STATE` - the VMO is mapped, is or has a clone, or has
+    ///   committed pages
+    pub fn set_cache_policy(&self, policy: CachePolicy) -> Result {
+        if self.mapping_count() != 0
+            || !self.children.lock().is_empty()
+            || self.parent.lock().is_some()
+        {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        if !self.flags.is_physical() && self.pages.committed_count() != 0 {
+            return Err(RX_ERR_BAD_STATE);
+        }
+
         *self.cache_policy.lock() = policy;
+        Ok(())
     }
 
     /// Get cache policy
@@ -448,6 +1215,23 @@ impl Vmo {
         *self.cache_policy.lock()
     }
 
+    /// Set the NUMA placement of pages committed from now on
+    ///
+    /// Pages already committed stay where they are.
+    pub fn set_numa_policy(&self, policy: NumaPolicy) {
+        *self.numa_policy.lock() = policy;
+    }
+
+    /// Get NUMA placement policy
+    pub fn numa_policy(&self) -> NumaPolicy {
+        *self.numa_policy.lock()
+    }
+
+    /// Commit the page at `index` on the node the NUMA policy picks
+    fn commit(&self, index: usize) -> Result<PAddr> {
+        self.pages.allocate_on(index, self.numa_policy().node_for(index))
+    }
+
     /// Clone this VMO (COW)
     ///
     /// # Arguments
@@ -461,9 +1245,11 @@ impl Vmo {
         }
 
         let page_count = size / 4096;
+        let koid = alloc_koid();
 
         let vmo = Self {
             id: alloc_vmo_id(),
+            koid,
             size: AtomicU64::new(size as u64),
             flags: VmoFlags::COW,
             pages: PageMap::new(page_count),
@@ -474,8 +1260,14 @@ impl Vmo {
             })),
             children: Mutex::new(Vec::new()),
             cache_policy: Mutex::new(self.cache_policy()),
+            numa_policy: Mutex::new(self.numa_policy()),
             ref_count: AtomicUsize::new(1),
             mapped_aspaces: Mutex::new(alloc::collections::BTreeSet::new()),
+            mapping_count: AtomicUsize::new(0),
+            aspace_mappings: Mutex::new(Vec::new()),
+            discard: Mutex::new(DiscardState::default()),
+            pins: Mutex::new(BTreeMap::new()),
+            tracked: Tracked::new(koid, ObjectType::Vmo),
         };
 
         // Add as child
@@ -519,6 +1311,21 @@ impl Vmo {
         self.mapped_aspaces.lock().remove(&aspace_id);
     }
 
+    /// Record a new VMAR mapping of this VMO
+    pub fn mapping_added(&self) {
+        self.mapping_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that a VMAR mapping of this VMO was removed
+    pub fn mapping_removed(&self) {
+        self.mapping_count.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Number of VMAR mappings of this VMO
+    pub fn mapping_count(&self) -> usize {
+        self.mapping_count.load(Ordering::Relaxed)
+    }
+
     /// Get share count
     ///
     /// Returns the number of unique address spaces this VMO is mapped into.
@@ -569,10 +1376,186 @@ mod tests {
         assert_eq!(vmo.size(), 0x2000);
     }
 
+    #[test]
+    fn test_vmo_resize_frees_pages() {
+        let vmo = Vmo::create(0x3000, VmoFlags::RESIZABLE).unwrap();
+        vmo.pages.insert(0, 0x2000_0000);
+        vmo.pages.insert(2, 0x2000_2000);
+
+        // Pages past the new end are dropped
+        assert!(vmo.resize(0x2000).is_ok());
+        assert_eq!(vmo.pages.committed_count(), 1);
+        assert_eq!(vmo.pages.total_count(), 2);
+
+        // Growing adds uncommitted pages
+        assert!(vmo.resize(0x4000).is_ok());
+        assert_eq!(vmo.pages.committed_count(), 1);
+        assert_eq!(vmo.pages.total_count(), 4);
+    }
+
+    #[test]
+    fn test_vmo_op_range() {
+        let vmo = Vmo::create(0x4000, VmoFlags::empty).unwrap();
+        vmo.pages.insert(1, 0x2000_1000);
+        vmo.pages.insert(3, 0x2000_3000);
+
+        assert_eq!(vmo.op_range(VmoOp::Commit, 0x3000, 0x2000), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(vmo.op_range(VmoOp::Commit, usize::MAX, 2), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(vmo.op_range(VmoOp::Decommit, 0x800, 0x1000), Err(RX_ERR_INVALID_ARGS));
+        assert!(vmo.op_range(VmoOp::Decommit, 0x4000, 0).is_ok());
+
+        assert!(vmo.op_range(VmoOp::Decommit, 0, 0x2000).is_ok());
+        assert_eq!(vmo.pages.committed_count(), 1);
+        assert!(vmo.pages.get(3).is_some());
+
+        // Physical VMOs are always committed
+        let vmo = Vmo::create_physical(0x1000_0000, 0x2000).unwrap();
+        assert!(vmo.op_range(VmoOp::Commit, 0, 0x2000).is_ok());
+        assert_eq!(vmo.op_range(VmoOp::Decommit, 0, 0x1000), Err(RX_ERR_NOT_SUPPORTED));
+        assert_eq!(vmo.pages.committed_count(), 2);
+
+        assert_eq!(VmoOp::from_raw(10), Some(VmoOp::Zero));
+        assert_eq!(VmoOp::from_raw(3), None);
+    }
+
+    #[test]
+    fn test_vmo_discard() {
+        let vmo = Vmo::create(0x2000, VmoFlags::DISCARDABLE).unwrap();
+        vmo.pages.insert(0, 0x2000_0000);
+
+        // Locked pages stay
+        assert_eq!(vmo.op_range(VmoOp::Lock, 0, 0x2000), Ok(()));
+        assert_eq!(vmo.discard(), 0);
+        assert_eq!(vmo.op_range(VmoOp::Unlock, 0, 0x2000), Ok(()));
+        assert_eq!(vmo.op_range(VmoOp::Unlock, 0, 0x2000), Err(RX_ERR_BAD_STATE));
+
+        // Unlocked pages go, and the next lock says so
+        assert_eq!(vmo.discard(), 1);
+        assert_eq!(vmo.pages.committed_count(), 0);
+        assert_eq!(vmo.op_range(VmoOp::TryLock, 0, 0x2000), Err(RX_ERR_NOT_FOUND));
+        assert_eq!(vmo.lock(), Ok(true));
+        assert_eq!(vmo.lock(), Ok(false));
+
+        // Contents survived, so a try-lock succeeds
+        assert_eq!(vmo.op_range(VmoOp::TryLock, 0, 0x2000), Ok(()));
+        for _ in 0..3 {
+            assert_eq!(vmo.unlock(), Ok(()));
+        }
+        assert_eq!(vmo.unlock(), Err(RX_ERR_BAD_STATE));
+
+        // Whole VMO only
+        assert_eq!(vmo.op_range(VmoOp::Unlock, 0, 0x1000), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(vmo.op_range(VmoOp::TryLock, 0x1000, 0x1000), Err(RX_ERR_INVALID_ARGS));
+
+        let vmo = Vmo::create(0x1000, VmoFlags::empty).unwrap();
+        vmo.pages.insert(0, 0x2000_0000);
+        assert_eq!(vmo.discard(), 0);
+        assert_eq!(vmo.op_range(VmoOp::Lock, 0, 0x1000), Err(RX_ERR_NOT_SUPPORTED));
+    }
+
+    #[test]
+    fn test_vmo_transfer_data() {
+        let src = Vmo::create(0x3000, VmoFlags::empty).unwrap();
+        let dst = Vmo::create(0x3000, VmoFlags::empty).unwrap();
+        src.pages.insert(0, 0x2000_0000);
+        src.pages.insert(2, 0x2000_2000);
+        dst.pages.insert(1, 0x3000_1000);
+        let moved = src.pages.get(2).unwrap();
+
+        // Pages 1 and 2 of src land on 0 and 1 of dst, dst's old page 1 is
+        // dropped for src's uncommitted page 1
+        assert_eq!(dst.transfer_data(0, 0x2000, &src, 0x1000), Ok(()));
+        assert_eq!(dst.pages.get(1), Some(moved));
+        assert!(dst.pages.get(0).is_none());
+        assert_eq!(dst.pages.committed_count(), 1);
+        assert!(src.pages.get(2).is_none());
+        assert_eq!(src.pages.committed_count(), 1);
+
+        assert_eq!(dst.transfer_data(0x800, 0x1000, &src, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(dst.transfer_data(0x2000, 0x2000, &src, 0), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(src.transfer_data(0x1000, 0x2000, &src, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(src.transfer_data(0x2000, 0x1000, &src, 0), Ok(()));
+
+        let phys = Vmo::create_physical(0x1000_0000, 0x1000).unwrap();
+        assert_eq!(phys.transfer_data(0, 0x1000, &src, 0), Err(RX_ERR_NOT_SUPPORTED));
+        let _clone = src.clone(0, 0x1000).unwrap();
+        assert_eq!(dst.transfer_data(0, 0x1000, &src, 0), Err(RX_ERR_BAD_STATE));
+    }
+
+    #[test]
+    fn test_vmo_pin() {
+        let vmo = Vmo::create(0x3000, VmoFlags::RESIZABLE).unwrap();
+        vmo.pages.insert(1, 0x2000_1000);
+        vmo.pages.insert(2, 0x2000_2000);
+
+        assert_eq!(vmo.pin(0x1000, 0x800), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(vmo.pin(0x2000, 0x2000), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(vmo.pin(0x1000, 0x2000), Ok(()));
+        assert_eq!(vmo.pin(0x2000, 0x1000), Ok(()));
+        assert_eq!(vmo.pinned_count(), 2);
+
+        // Pinned pages can't go away
+        assert_eq!(vmo.op_range(VmoOp::Decommit, 0x1000, 0x1000), Err(RX_ERR_BAD_STATE));
+        assert_eq!(vmo.resize(0x1000), Err(RX_ERR_BAD_STATE));
+        assert_eq!(vmo.pages.get(2), Some(0x2000_2000));
+
+        assert_eq!(vmo.unpin(0x1000, 0x2000), Ok(()));
+        assert!(!vmo.is_pinned(1, 2));
+        assert_eq!(vmo.resize(0x2000), Err(RX_ERR_BAD_STATE));
+        assert_eq!(vmo.unpin(0x2000, 0x1000), Ok(()));
+        assert_eq!(vmo.unpin(0x1000, 0x1000), Err(RX_ERR_BAD_STATE));
+        assert_eq!(vmo.pinned_count(), 0);
+    }
+
+    #[test]
+    fn test_vmo_create_physical() {
+        let vmo = Vmo::create_physical(0x1000_0000, 0x3000).unwrap();
+        assert!(vmo.flags.is_physical());
+        assert_eq!(vmo.pages.committed_count(), 3);
+        assert_eq!(vmo.cache_policy(), CachePolicy::Uncached);
+        assert!(Vmo::create_physical(0x1000_0800, 0x1000).is_err());
+    }
+
     #[test]
     fn test_cache_policy() {
         let policy = CachePolicy::Uncached;
         assert_eq!(CachePolicy::from_raw(1), policy);
         assert_eq!(policy.into_raw(), 1);
+        assert_eq!(CachePolicy::try_from_raw(2), Some(CachePolicy::WriteCombining));
+        assert_eq!(CachePolicy::try_from_raw(4), None);
+    }
+
+    #[test]
+    fn test_numa_policy() {
+        let vmo = Vmo::create(0x2000, VmoFlags::empty).unwrap();
+        assert_eq!(vmo.numa_policy(), NumaPolicy::Local);
+
+        vmo.set_numa_policy(NumaPolicy::Interleave);
+        assert_eq!(vmo.numa_policy(), NumaPolicy::Interleave);
+        assert_eq!(vmo.clone(0, 0x1000).unwrap().numa_policy(), NumaPolicy::Interleave);
+    }
+
+    #[test]
+    fn test_set_cache_policy() {
+        // Physical VMOs change policy while unmapped
+        let vmo = Vmo::create_physical(0x1000_0000, 0x1000).unwrap();
+        assert!(vmo.set_cache_policy(CachePolicy::WriteCombining).is_ok());
+        assert_eq!(vmo.cache_policy(), CachePolicy::WriteCombining);
+
+        vmo.mapping_added();
+        assert_eq!(vmo.set_cache_policy(CachePolicy::Uncached), Err(RX_ERR_BAD_STATE));
+        vmo.mapping_removed();
+        assert!(vmo.set_cache_policy(CachePolicy::Uncached).is_ok());
+
+        // Paged VMOs only before pages are committed
+        let vmo = Vmo::create(0x2000, VmoFlags::empty).unwrap();
+        assert!(vmo.set_cache_policy(CachePolicy::Uncached).is_ok());
+        vmo.pages.insert(0, 0x2000_0000);
+        assert_eq!(vmo.set_cache_policy(CachePolicy::Default), Err(RX_ERR_BAD_STATE));
+
+        // Nor once cloned
+        let vmo = Vmo::create(0x2000, VmoFlags::empty).unwrap();
+        let _clone = vmo.clone(0, 0x1000).unwrap();
+        assert_eq!(vmo.set_cache_policy(CachePolicy::Uncached), Err(RX_ERR_BAD_STATE));
     }
 }
@@ -0,0 +1,357 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Panic
+//!
+//! The path taken by `panic!` and by fatal exceptions:
+//!
+//! 1. Capture the registers of the panicking context before anything
+//!    else can clobber them
+//! 2. Stop the other CPUs and mask interrupts
+//! 3. Print the message and location, the captured registers, the
+//!    faulting iframe (if a fatal exception got us here) and a
+//!    symbolized backtrace
+//! 4. Seal the crash log, so the output can be collected after a warm
+//!    reboot, then halt or reboot the machine through the platform
+//!
+//! Output goes straight to the console with `print_internal`, which never
+//! takes a lock, so a panic while the console or debuglog lock is held
+//! still reaches the serial port.
+//!
+//! By default a panic reboots the machine. Boot with
+//! `kernel.halt-on-panic=true` to keep it halted for inspection.
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::kernel::cmdline;
+use crate::kernel::debug::{print_internal, LogWriter};
+use crate::kernel::lib::backtrace::{self, Backtrace};
+use crate::kernel::lib::crashlog;
+use crate::kernel::percpu;
+use crate::platform;
+
+/// Command line option to halt instead of rebooting
+pub const CMDLINE_HALT_ON_PANIC: &str = "kernel.halt-on-panic";
+
+/// Maximum number of registers in a dump
+const MAX_REGS: usize = 24;
+
+/// Set by the first CPU to panic
+static PANIC_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Registers captured at the point of the panic
+#[derive(Clone, Copy)]
+pub struct PanicRegs {
+    regs: [(&'static str, u64); MAX_REGS],
+    count: usize,
+
+    /// Frame pointer of the panicking function
+    pub fp: usize,
+}
+
+impl PanicRegs {
+    const fn new() -> Self {
+        Self {
+            regs: [("", 0); MAX_REGS],
+            count: 0,
+            fp: 0,
+        }
+    }
+
+    fn push(&mut self, name: &'static str, value: u64) {
+        if self.count < MAX_REGS {
+            self.regs[self.count] = (name, value);
+            self.count += 1;
+        }
+    }
+
+    /// Captured registers as (name, value) pairs
+    pub fn regs(&self) -> &[(&'static str, u64)] {
+        &self.regs[..self.count]
+    }
+
+    /// Capture the general and control registers of the caller
+    #[inline(always)]
+    pub fn capture() -> Self {
+        // One instruction per statement, so an output can never be
+        // allocated to a register that is still to be read
+        macro_rules! read {
+            ($insn:literal) => {{
+                let value: u64;
+                core::arch::asm!($insn, out(reg) value, options(nomem, nostack));
+                value
+            }};
+        }
+
+        let mut r = Self::new();
+
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            use crate::kernel::arch::amd64::registers::{x86_get_cr2, x86_get_cr3, x86_get_cr4};
+
+            let rflags: u64;
+            core::arch::asm!("pushfq", "pop {}", out(reg) rflags, options(nomem));
+
+            r.push("rip", read!("lea {}, [rip]"));
+            r.push("rsp", read!("mov {}, rsp"));
+            r.push("rbp", read!("mov {}, rbp"));
+            r.push("rflags", rflags);
+            r.push("rbx", read!("mov {}, rbx"));
+            r.push("r12", read!("mov {}, r12"));
+            r.push("r13", read!("mov {}, r13"));
+            r.push("r14", read!("mov {}, r14"));
+            r.push("r15", read!("mov {}, r15"));
+            r.push("cr2", x86_get_cr2());
+            r.push("cr3", x86_get_cr3());
+            r.push("cr4", x86_get_cr4());
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            r.push("lr", read!("mov {}, x30"));
+            r.push("sp", read!("mov {}, sp"));
+            r.push("fp", read!("mov {}, x29"));
+            r.push("x19", read!("mov {}, x19"));
+            r.push("x20", read!("mov {}, x20"));
+            r.push("x21", read!("mov {}, x21"));
+            r.push("x22", read!("mov {}, x22"));
+            r.push("x23", read!("mov {}, x23"));
+            r.push("x24", read!("mov {}, x24"));
+            r.push("x25", read!("mov {}, x25"));
+            r.push("x26", read!("mov {}, x26"));
+            r.push("x27", read!("mov {}, x27"));
+            r.push("x28", read!("mov {}, x28"));
+            r.push("elr_el1", read!("mrs {}, elr_el1"));
+            r.push("spsr_el1", read!("mrs {}, spsr_el1"));
+            r.push("esr_el1", read!("mrs {}, esr_el1"));
+            r.push("far_el1", read!("mrs {}, far_el1"));
+            r.push("sctlr_el1", read!("mrs {}, sctlr_el1"));
+            r.push("daif", read!("mrs {}, daif"));
+        }
+
+        #[cfg(target_arch = "riscv64")]
+        unsafe {
+            r.push("ra", read!("mv {}, ra"));
+            r.push("sp", read!("mv {}, sp"));
+            r.push("s0", read!("mv {}, s0"));
+            r.push("gp", read!("mv {}, gp"));
+            r.push("tp", read!("mv {}, tp"));
+            r.push("s1", read!("mv {}, s1"));
+            r.push("s2", read!("mv {}, s2"));
+            r.push("s3", read!("mv {}, s3"));
+            r.push("s4", read!("mv {}, s4"));
+            r.push("s5", read!("mv {}, s5"));
+            r.push("s6", read!("mv {}, s6"));
+            r.push("s7", read!("mv {}, s7"));
+            r.push("sstatus", read!("csrr {}, sstatus"));
+            r.push("sepc", read!("csrr {}, sepc"));
+            r.push("scause", read!("csrr {}, scause"));
+            r.push("stval", read!("csrr {}, stval"));
+            r.push("satp", read!("csrr {}, satp"));
+        }
+
+        r.fp = backtrace::current_frame_pointer();
+        r
+    }
+
+    /// Write the registers to `w`, three per line
+    pub fn write_to(&self, w: &mut dyn Write) -> core::fmt::Result {
+        for row in self.regs().chunks(3) {
+            for &(name, value) in row {
+                write!(w, " {:>9} {:#018x}", name, value)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Entry point from the `#[panic_handler]`
+#[inline(never)]
+pub fn kernel_panic(info: &PanicInfo) -> ! {
+    let regs = PanicRegs::capture();
+    let location = info.location().map(|loc| (loc.file(), loc.line(), loc.column()));
+    panic_with_regs(&regs, location, platform::HALT_REASON_SW_PANIC, format_args!("{}", info.message()), None)
+}
+
+/// Panic with an explicit message and location
+///
+/// For callers that report a location other than their own, such as
+/// assertion handlers.
+#[inline(never)]
+pub fn panic_at(message: &str, file: &str, line: u32, col: u32) -> ! {
+    let regs = PanicRegs::capture();
+    panic_with_regs(&regs, Some((file, line, col)), platform::HALT_REASON_SW_PANIC, format_args!("{}", message), None)
+}
+
+/// Panic with a halt reason other than `HALT_REASON_SW_PANIC`
+///
+/// For the kernel's own failure detectors, such as the watchdog, so the
+/// crash log records what caught the failure.
+#[inline(never)]
+pub fn panic_for_reason(reason: u32, message: core::fmt::Arguments) -> ! {
+    let regs = PanicRegs::capture();
+    panic_with_regs(&regs, None, reason, message, None)
+}
+
+/// Panic with a report of its own, printed after the message
+///
+/// For detectors with more to say than fits in a message, such as KASAN's
+/// shadow memory dump. The report goes to the crash log with the rest.
+#[inline(never)]
+pub fn panic_with_report(
+    message: core::fmt::Arguments,
+    report: &dyn Fn(&mut dyn Write) -> core::fmt::Result,
+) -> ! {
+    let regs = PanicRegs::capture();
+    panic_with_regs(&regs, None, platform::HALT_REASON_SW_PANIC, message, Some(report))
+}
+
+fn panic_with_regs(
+    regs: &PanicRegs,
+    location: Option<(&str, u32, u32)>,
+    reason: u32,
+    message: core::fmt::Arguments,
+    report: Option<&dyn Fn(&mut dyn Write) -> core::fmt::Result>,
+) -> ! {
+    if PANIC_STARTED.swap(true, Ordering::AcqRel) {
+        // Panicked while panicking, or a second CPU panicked: say so and
+        // stop without touching anything the first panic might be using
+        print_internal("\n*** nested panic, halting ***\n");
+        platform::platform_halt_cpu();
+    }
+
+    platform::platform_panic_start();
+    crate::kernel::lib::debuglog::dlog_bluescreen_init();
+    crashlog::begin(reason);
+
+    let mut w = LogWriter;
+    let _ = writeln!(w, "\n*** KERNEL PANIC on CPU {} ***", percpu::current_cpu_num());
+    match location {
+        Some((file, line, col)) => {
+            let _ = writeln!(w, "at {}:{}:{}", file, line, col);
+        }
+        None => {
+            let _ = writeln!(w, "at unknown location");
+        }
+    }
+    let _ = writeln!(w, "{}", message);
+    if let Some(report) = report {
+        let _ = writeln!(w);
+        let _ = report(&mut w);
+    }
+
+    dump_state(regs);
+
+    let action = if cmdline::cmdline_get_bool(CMDLINE_HALT_ON_PANIC, false) {
+        platform::HALT_ACTION_HALT
+    } else {
+        platform::HALT_ACTION_REBOOT
+    };
+    crashlog::finish();
+    platform::platform_halt(reason, action);
+}
+
+/// Print registers, the fault iframe and backtraces
+fn dump_state(regs: &PanicRegs) {
+    let mut w = LogWriter;
+
+    let _ = writeln!(w, "\nRegisters:");
+    let _ = regs.write_to(&mut w);
+
+    let iframe = crashlog::iframe();
+    if !iframe.is_null() {
+        let _ = writeln!(w, "\nFault frame:");
+        unsafe {
+            dump_iframe(iframe);
+            let _ = writeln!(w, "\nFault backtrace:");
+            iframe_backtrace(iframe).print();
+        }
+    }
+
+    let _ = writeln!(w, "\nBacktrace:");
+    unsafe { Backtrace::from_frame(regs.fp).print() };
+    let _ = writeln!(w);
+}
+
+/// Print an architecture iframe recorded in the crashlog
+unsafe fn dump_iframe(iframe: *const u8) {
+    #[cfg(target_arch = "x86_64")]
+    crate::kernel::arch::amd64::faults::dump_fault_frame(
+        &*(iframe as *const crate::kernel::arch::amd64::X86Iframe),
+    );
+
+    #[cfg(target_arch = "aarch64")]
+    crate::kernel::arch::arm64::exceptions_c::dump_iframe(
+        &*(iframe as *const crate::kernel::arch::arm64::arm64_iframe_long),
+    );
+
+    #[cfg(target_arch = "riscv64")]
+    crate::kernel::arch::riscv64::exceptions_c::dump_iframe(
+        &*(iframe as *const crate::kernel::arch::riscv64::exceptions_c::RiscvIframe),
+    );
+}
+
+/// Backtrace of the context an iframe interrupted
+unsafe fn iframe_backtrace(iframe: *const u8) -> Backtrace {
+    #[cfg(target_arch = "x86_64")]
+    let (pc, fp) = {
+        let frame = &*(iframe as *const crate::kernel::arch::amd64::X86Iframe);
+        (frame.rip as usize, frame.rbp as usize)
+    };
+
+    #[cfg(target_arch = "aarch64")]
+    let (pc, fp) = {
+        let frame = &*(iframe as *const crate::kernel::arch::arm64::arm64_iframe_long);
+        (frame.elr as usize, frame.r[29] as usize)
+    };
+
+    #[cfg(target_arch = "riscv64")]
+    let (pc, fp) = {
+        let frame = &*(iframe as *const crate::kernel::arch::riscv64::exceptions_c::RiscvIframe);
+        (frame.pc as usize, frame.s0 as usize)
+    };
+
+    Backtrace::from_context(pc, fp)
+}
+
+/// Whether a panic is in progress
+pub fn panic_in_progress() -> bool {
+    PANIC_STARTED.load(Ordering::Acquire)
+}
+
+/// Print the current backtrace without panicking
+pub fn dump_backtrace() {
+    backtrace::print_backtrace();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_registers() {
+        let regs = PanicRegs::capture();
+        assert!(regs.regs().len() > 4);
+        assert!(regs.regs().len() <= MAX_REGS);
+    }
+
+    #[test]
+    fn test_register_dump_format() {
+        let mut regs = PanicRegs::new();
+        regs.push("a", 1);
+        regs.push("b", 2);
+        regs.push("c", 3);
+        regs.push("d", 4);
+
+        let mut out = alloc::string::String::new();
+        regs.write_to(&mut out).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.contains("0x0000000000000004"));
+    }
+}
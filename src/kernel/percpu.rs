@@ -28,7 +28,7 @@
 
 
 use crate::kernel::thread::ThreadId;
-use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 
 // Import logging macros
 use crate::{log_debug, log_info};
@@ -64,8 +64,22 @@ pub struct PerCpu {
     /// CPU state
     pub state: AtomicU8,
 
+    /// Preemption requested from interrupt context, taken on the
+    /// outermost interrupt exit (see [`crate::kernel::irq`])
+    pub irq_preempt: AtomicBool,
+
+    /// Interrupt nesting depth, 0 outside interrupt context
+    pub irq_depth: AtomicU32,
+
+    /// Preemption-disable depth, 0 when the thread may be preempted (see
+    /// [`crate::kernel::preempt`])
+    pub preempt_depth: AtomicU32,
+
+    /// A preemption arrived while disabled, taken when the depth drops to 0
+    pub preempt_deferred: AtomicBool,
+
     /// Reserved for future use
-    _reserved: [u8; 59],
+    _reserved: [u8; 44],
 }
 
 /// CPU states
@@ -99,7 +113,11 @@ impl PerCpu {
             cpu_num: 0,
             current_thread: AtomicU64::new(0),
             state: AtomicU8::new(CpuState::Offline as u8),
-            _reserved: [0; 59],
+            irq_preempt: AtomicBool::new(false),
+            irq_depth: AtomicU32::new(0),
+            preempt_depth: AtomicU32::new(0),
+            preempt_deferred: AtomicBool::new(false),
+            _reserved: [0; 44],
         }
     }
 
@@ -109,6 +127,8 @@ impl PerCpu {
         self.cpu_num = cpu_num;
         self.current_thread.store(0, Ordering::Release);
         self.state.store(CpuState::Uninitialized as u8, Ordering::Release);
+        self.irq_preempt.store(false, Ordering::Relaxed);
+        self.irq_depth.store(0, Ordering::Relaxed);
     }
 
     /// Get the current thread ID
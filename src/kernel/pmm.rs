@@ -21,6 +21,40 @@
 //! - Multiple memory arenas can be registered (e.g., low memory, high memory)
 //! - Pages are tracked in `Page` structures with state information
 //!
+//! Every allocation and free reports to [`pressure`], which tracks the
+//! memory pressure level and reclaims memory when it runs low.
+//!
+//! # NUMA Nodes
+//!
+//! Arenas are split at NUMA node boundaries as they are added (see
+//! [`numa`]), so each arena is on a single node.
+//! [`pmm_alloc_page_on_node`] tries the arenas on the requested node
+//! first and falls back to the others, counting the fallback in
+//! `vm.numa.alloc.remote`.
+//!
+//! # Page Aging
+//!
+//! Each [`Page`] keeps an age: the number of accessed/dirty harvests in a
+//! row that found it unused. The VM layer feeds harvests in through
+//! [`pmm_page_harvested`]; eviction policy reads the result back with
+//! [`pmm_page_age`]. The age resets when the page is freed.
+//!
+//! # Pre-zeroed Pages
+//!
+//! Callers that need zeroed memory, such as anonymous faults, use
+//! [`pmm_alloc_zeroed_page`]. It takes pages from a pool of zeroed pages
+//! kept filled while memory is plentiful, so the zeroing usually happens
+//! off the fault path:
+//!
+//! - a system workqueue item tops the pool up to [`ZERO_POOL_TARGET`]
+//!   when allocations run it low
+//! - idle CPUs scrub further pages into it, up to
+//!   [`ZERO_POOL_IDLE_TARGET`], one page per [`pmm_scrub_idle`] call
+//!
+//! Reclaim empties the pool with [`pmm_drain_zero_pages`]. The
+//! `vm.zero_page.alloc.prezeroed` and `vm.zero_page.alloc.on_demand`
+//! counters show how often the pool spared the caller the zeroing.
+//!
 //! # Usage
 //!
 //! ```rust
@@ -40,6 +74,20 @@ use crate::rustux::types::err::*;
 use crate::rustux::errors::*;
 // Use fully qualified Result to avoid ambiguity
 use crate::rustux::types::Result;
+use crate::kernel::numa::{self, NUMA_NODE_ANY};
+use crate::kernel::sync::Mutex;
+use crate::kernel::vm::pressure;
+use crate::kernel::workqueue::{self, Work};
+use alloc::vec::Vec;
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(ZERO_ALLOC_PREZEROED, "vm.zero_page.alloc.prezeroed");
+KCOUNTER!(ZERO_ALLOC_ON_DEMAND, "vm.zero_page.alloc.on_demand");
+KCOUNTER!(ZERO_SCRUBBED_IDLE, "vm.zero_page.scrubbed.idle");
+KCOUNTER!(ZERO_SCRUBBED_WORK, "vm.zero_page.scrubbed.work");
+KCOUNTER!(NUMA_ALLOC_REMOTE, "vm.numa.alloc.remote");
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use core::sync::atomic::{compiler_fence, fence};
 
@@ -53,7 +101,7 @@ pub const PAGE_SIZE_SHIFT: u8 = 12;
 pub const PAGE_MASK: usize = PAGE_SIZE - 1;
 
 /// Maximum number of physical memory arenas
-const MAX_ARENAS: usize = 8;
+const MAX_ARENAS: usize = 16;
 
 /// Check if an address is page-aligned
 #[inline]
@@ -121,9 +169,14 @@ pub struct ArenaInfo {
 
     /// Size in bytes
     pub size: usize,
+
+    /// NUMA node
+    pub node: u32,
 }
 
 impl ArenaInfo {
+    const EMPTY: Self = Self::new(&[], 0, 0, 0, 0);
+
     /// Create a new arena info structure
     pub const fn new(name: &[u8], flags: u32, priority: u32, base: PAddr, size: usize) -> Self {
         let mut name_bytes = [0u8; 16];
@@ -139,6 +192,7 @@ impl ArenaInfo {
             priority,
             base,
             size,
+            node: 0,
         }
     }
 
@@ -179,6 +233,22 @@ pub struct Page {
 
     /// Page index within arena
     pub page_index: u32,
+
+    /// Harvests in a row that found the page unaccessed, saturating
+    pub age: u8,
+
+    /// Some harvest found the page written since it was last cleaned
+    pub dirty: bool,
+}
+
+/// Age and dirty state of a page, from [`pmm_page_age`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PageAge {
+    /// Harvests in a row that found the page unaccessed
+    pub age: u8,
+
+    /// Written since it was last cleaned
+    pub dirty: bool,
 }
 
 impl Page {
@@ -190,9 +260,23 @@ impl Page {
             ref_count: 0,
             arena_index,
             page_index,
+            age: 0,
+            dirty: false,
         }
     }
 
+    /// Fold one harvest's findings into the page's age
+    pub fn record_harvest(&mut self, accessed: bool, dirty: bool) {
+        self.age = if accessed { 0 } else { self.age.saturating_add(1) };
+        self.dirty |= dirty;
+    }
+
+    /// Reset the age, as for a newly allocated page
+    fn reset_age(&mut self) {
+        self.age = 0;
+        self.dirty = false;
+    }
+
     /// Check if this page is free
     pub fn is_free(&self) -> bool {
         self.state == PageState::Free
@@ -273,6 +357,7 @@ impl Arena {
                             if let Some(pages) = &mut self.pages {
                                 pages[index].state = PageState::Allocated;
                                 pages[index].ref_count = 1;
+                                pages[index].reset_age();
                                 return Some(self.info.base + (index as PAddr) * PAGE_SIZE as PAddr);
                             }
                         }
@@ -309,6 +394,7 @@ impl Arena {
         if let Some(pages) = &mut self.pages {
             pages[index].state = PageState::Free;
             pages[index].ref_count = 0;
+            pages[index].reset_age();
         }
 
         self.free_count.fetch_add(1, Ordering::Relaxed);
@@ -415,6 +501,7 @@ impl Arena {
                         for page_idx in start_index..(start_index + count) {
                             pages[page_idx].state = PageState::Allocated;
                             pages[page_idx].ref_count = 1;
+                            pages[page_idx].reset_age();
                         }
                     }
 
@@ -466,6 +553,7 @@ impl Arena {
             for page_idx in start_index..(start_index + count) {
                 pages[page_idx].state = PageState::Free;
                 pages[page_idx].ref_count = 0;
+                pages[page_idx].reset_age();
             }
         }
 
@@ -475,69 +563,14 @@ impl Arena {
 }
 
 /// Global PMM state
-static mut ARENAS: [Arena; MAX_ARENAS] = [
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-    Arena::new(ArenaInfo {
-        name: [0; 16],
-        flags: 0,
-        priority: 0,
-        base: 0,
-        size: 0,
-    }),
-];
+static mut ARENAS: [Arena; MAX_ARENAS] = [const { Arena::new(ArenaInfo::EMPTY) }; MAX_ARENAS];
 
 static mut NUM_ARENAS: usize = 0;
 
 /// Add a memory arena to the PMM
 ///
+/// A range that spans NUMA nodes is added as one arena per node.
+///
 /// # Arguments
 ///
 /// * `info` - Arena information (base address, size, flags)
@@ -550,6 +583,17 @@ static mut NUM_ARENAS: usize = 0;
 ///
 /// This function modifies global PMM state and should only be called during boot.
 pub unsafe fn pmm_add_arena(info: ArenaInfo) -> rx_status_t {
+    let mut status = RX_OK;
+    numa::split_range(info.base as u64, info.size as u64, |base, size, node| {
+        if status == RX_OK {
+            status = add_one_arena(ArenaInfo { base: base as PAddr, size: size as usize, node, ..info });
+        }
+    });
+    status
+}
+
+/// Add an arena on a single node
+unsafe fn add_one_arena(info: ArenaInfo) -> rx_status_t {
     if NUM_ARENAS >= MAX_ARENAS {
         return RX_ERR_NO_RESOURCES;
     }
@@ -609,19 +653,37 @@ pub unsafe fn pmm_add_arena(info: ArenaInfo) -> rx_status_t {
 ///
 /// Physical address of the allocated page, or an error
 pub fn pmm_alloc_page(flags: u32) -> Result<PAddr> {
+    pmm_alloc_page_on_node(NUMA_NODE_ANY, flags)
+}
+
+/// Allocate a single physical page, preferably on NUMA `node`
+///
+/// Arenas on `node` are tried first, then the rest. `NUMA_NODE_ANY`
+/// takes the first free page of any node.
+pub fn pmm_alloc_page_on_node(node: u32, flags: u32) -> Result<PAddr> {
     let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
 
-    // Try to allocate from matching arenas
-    for arena in arenas {
-        if flags == PMM_ALLOC_FLAG_LOW_MEM && (arena.info.flags & ARENA_FLAG_LOW_MEM) == 0 {
-            continue;
-        }
+    // Arenas on the node, then the others
+    for local in [true, false] {
+        for arena in arenas.iter_mut() {
+            if flags == PMM_ALLOC_FLAG_LOW_MEM && (arena.info.flags & ARENA_FLAG_LOW_MEM) == 0 {
+                continue;
+            }
+            if (node == NUMA_NODE_ANY || arena.info.node == node) != local {
+                continue;
+            }
 
-        if let Some(paddr) = arena.alloc_page() {
-            return Ok(paddr);
+            if let Some(paddr) = arena.alloc_page() {
+                if !local {
+                    NUMA_ALLOC_REMOTE.add(1);
+                }
+                pressure::update();
+                return Ok(paddr);
+            }
         }
     }
 
+    pressure::allocation_failed();
     Err(RX_ERR_NO_MEMORY)
 }
 
@@ -655,10 +717,12 @@ pub fn pmm_alloc_contiguous(count: usize, flags: u32, align_log2: u8) -> Result<
         }
 
         if let Some(paddr) = arena.alloc_contiguous(count, align_log2) {
+            pressure::update();
             return Ok(paddr);
         }
     }
 
+    pressure::allocation_failed();
     Err(RX_ERR_NO_MEMORY)
 }
 
@@ -678,7 +742,10 @@ pub fn pmm_free_page(paddr: PAddr) -> rx_status_t {
     for arena in arenas {
         if paddr >= arena.info.base && paddr < arena.info.end() {
             return match arena.free_page(paddr) {
-                Ok(()) => RX_OK,
+                Ok(()) => {
+                    pressure::update();
+                    RX_OK
+                }
                 Err(e) => e,
             };
         }
@@ -709,7 +776,11 @@ pub fn pmm_free_contiguous(paddr: PAddr, count: usize) -> rx_status_t {
 
     for arena in arenas {
         if paddr >= arena.info.base && end_addr <= arena.info.end() {
-            return arena.free_contiguous(paddr, count);
+            let status = arena.free_contiguous(paddr, count);
+            if status == RX_OK {
+                pressure::update();
+            }
+            return status;
         }
     }
 
@@ -740,11 +811,223 @@ pub fn pmm_count_total_pages() -> u64 {
     count
 }
 
+/// Free and total pages on NUMA `node`, as (free, total)
+pub fn pmm_count_node_pages(node: u32) -> (u64, u64) {
+    let arenas = unsafe { &ARENAS[..NUM_ARENAS] };
+    arenas
+        .iter()
+        .filter(|arena| arena.info.node == node)
+        .fold((0, 0), |(free, total), arena| (free + arena.free_count(), total + arena.total_count()))
+}
+
+/// NUMA node of the arena holding `paddr`, if the PMM manages it
+pub fn pmm_page_node(paddr: PAddr) -> Option<u32> {
+    let arenas = unsafe { &ARENAS[..NUM_ARENAS] };
+    arenas
+        .iter()
+        .find(|arena| paddr >= arena.info.base && paddr < arena.info.end())
+        .map(|arena| arena.info.node)
+}
+
+/// Label every arena with the NUMA node of its base address
+///
+/// For arenas added before the topology was known; [`numa::init`] calls
+/// it. Such an arena is not split, so one that spans nodes is counted
+/// entirely on the node it starts on.
+pub fn pmm_update_arena_nodes() {
+    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
+    for arena in arenas {
+        arena.info.node = numa::paddr_node(arena.info.base as u64);
+    }
+}
+
+/// Record one accessed/dirty harvest of the page at `paddr`
+///
+/// Ignored for addresses the PMM doesn't manage, such as device memory.
+pub fn pmm_page_harvested(paddr: PAddr, accessed: bool, dirty: bool) {
+    let page = paddr_to_page(paddr);
+    if !page.is_null() {
+        unsafe { (*page).record_harvest(accessed, dirty) };
+    }
+}
+
+/// Age and dirty state of the page at `paddr`, if the PMM manages it
+pub fn pmm_page_age(paddr: PAddr) -> Option<PageAge> {
+    let page = paddr_to_page(paddr);
+    if page.is_null() {
+        return None;
+    }
+
+    let page = unsafe { &*page };
+    Some(PageAge { age: page.age, dirty: page.dirty })
+}
+
+/// Mark the page at `paddr` clean, once its contents are written back
+pub fn pmm_page_cleaned(paddr: PAddr) {
+    let page = paddr_to_page(paddr);
+    if !page.is_null() {
+        unsafe { (*page).dirty = false };
+    }
+}
+
+// ============================================================================
+// Pre-zeroed Pages
+// ============================================================================
+
+/// Pre-zeroed pages the refill work keeps on hand
+pub const ZERO_POOL_TARGET: usize = 64;
+
+/// Pre-zeroed pages idle CPUs keep on hand
+pub const ZERO_POOL_IDLE_TARGET: usize = 4 * ZERO_POOL_TARGET;
+
+/// Pool size below which an allocation queues a refill
+const ZERO_POOL_LOW: usize = ZERO_POOL_TARGET / 4;
+
+/// Pre-zeroed free pages, owned by the PMM
+static ZERO_POOL: Mutex<Vec<PAddr>> = Mutex::new(Vec::new());
+
+/// Refills [`ZERO_POOL`] on the system workqueue
+static ZERO_REFILL: Work = Work::new(zero_pool_refill);
+
+/// Zero the page at `paddr` through the physmap
+fn zero_page(paddr: PAddr) {
+    unsafe {
+        let dst = crate::kernel::vm::phys_to_physmap(paddr as usize) as *mut u8;
+        core::ptr::write_bytes(dst, 0, PAGE_SIZE);
+    }
+}
+
+/// Allocate a single zeroed physical page
+///
+/// Served from the pre-zeroed pool when possible; otherwise allocates a
+/// page and zeroes it here. Pool pages may come from any arena, so
+/// `PMM_ALLOC_FLAG_LOW_MEM` requests always zero on demand.
+pub fn pmm_alloc_zeroed_page(flags: u32) -> Result<PAddr> {
+    pmm_alloc_zeroed_page_on_node(NUMA_NODE_ANY, flags)
+}
+
+/// Allocate a single zeroed physical page, preferably on NUMA `node`
+///
+/// Like [`pmm_alloc_zeroed_page`], taking a pool page only if it is on
+/// `node`.
+pub fn pmm_alloc_zeroed_page_on_node(node: u32, flags: u32) -> Result<PAddr> {
+    if flags == PMM_ALLOC_FLAG_ANY {
+        let (paddr, remaining) = {
+            let mut pool = ZERO_POOL.lock();
+            let index = if node == NUMA_NODE_ANY {
+                pool.len().checked_sub(1)
+            } else {
+                pool.iter().rposition(|&paddr| pmm_page_node(paddr) == Some(node))
+            };
+            (index.map(|index| pool.swap_remove(index)), pool.len())
+        };
+        if remaining < ZERO_POOL_LOW {
+            pmm_prefill_zero_pages();
+        }
+        if let Some(paddr) = paddr {
+            ZERO_ALLOC_PREZEROED.add(1);
+            return Ok(paddr);
+        }
+    }
+
+    let paddr = pmm_alloc_page_on_node(node, flags)?;
+    zero_page(paddr);
+    ZERO_ALLOC_ON_DEMAND.add(1);
+    Ok(paddr)
+}
+
+/// Queue a refill of the pre-zeroed pool
+pub fn pmm_prefill_zero_pages() {
+    workqueue::system().queue(&ZERO_REFILL);
+}
+
+/// Free every pre-zeroed page, returning how many were freed
+pub fn pmm_drain_zero_pages() -> usize {
+    let pages = core::mem::take(&mut *ZERO_POOL.lock());
+    for &paddr in &pages {
+        pmm_free_page(paddr);
+    }
+    pages.len()
+}
+
+/// Number of pre-zeroed pages on hand
+pub fn pmm_count_zero_pages() -> usize {
+    ZERO_POOL.lock().len()
+}
+
+/// Zero one free page into the pool if it holds fewer than `target`
+///
+/// Does nothing once memory pressure leaves `Normal`, so pre-zeroing
+/// never competes with real allocations for scarce memory. Returns
+/// whether a page was zeroed.
+fn zero_pool_add(target: usize) -> bool {
+    if pmm_count_zero_pages() >= target || pressure::level() != pressure::PressureLevel::Normal {
+        return false;
+    }
+    let Ok(paddr) = pmm_alloc_page(PMM_ALLOC_FLAG_ANY) else {
+        return false;
+    };
+    zero_page(paddr);
+    ZERO_POOL.lock().push(paddr);
+    true
+}
+
+/// Zero pages into the pool up to [`ZERO_POOL_TARGET`]
+fn zero_pool_refill(_work: &'static Work) {
+    while zero_pool_add(ZERO_POOL_TARGET) {
+        ZERO_SCRUBBED_WORK.add(1);
+    }
+}
+
+/// Scrub one page into the pool from an idle CPU
+///
+/// The idle loop calls this before sleeping and sleeps only once it
+/// returns false, so a wakeup waits for at most one page's zeroing.
+pub fn pmm_scrub_idle() -> bool {
+    let scrubbed = zero_pool_add(ZERO_POOL_IDLE_TARGET);
+    if scrubbed {
+        ZERO_SCRUBBED_IDLE.add(1);
+    }
+    scrubbed
+}
+
 /// Get the total amount of physical memory in bytes
 pub fn pmm_count_total_bytes() -> u64 {
     pmm_count_total_pages() * PAGE_SIZE as u64
 }
 
+/// `vm` console command
+fn cmd_vm(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    let arenas = unsafe { &ARENAS[..NUM_ARENAS] };
+
+    crate::println!("{:<16} {:>4} {:>18} {:>18} {:>10} {:>10}", "arena", "node", "base", "end", "pages", "free");
+    for arena in arenas {
+        let name = &arena.info.name;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        crate::println!(
+            "{:<16} {:>4} {:#18x} {:#18x} {:>10} {:>10}",
+            core::str::from_utf8(&name[..len]).unwrap_or("?"),
+            arena.info.node,
+            arena.info.base,
+            arena.info.end(),
+            arena.total_count(),
+            arena.free_count()
+        );
+    }
+
+    let total = pmm_count_total_pages();
+    let free = pmm_count_free_pages();
+    crate::println!(
+        "physical memory: {} MiB total, {} MiB free, {} MiB in use",
+        (total * PAGE_SIZE as u64) >> 20,
+        (free * PAGE_SIZE as u64) >> 20,
+        ((total - free) * PAGE_SIZE as u64) >> 20
+    );
+    0
+}
+
+crate::static_command!("vm", "show physical memory arenas and usage", cmd_vm);
+
 /// Allocate memory for page structures or bitmap
 ///
 /// This is a helper function that allocates memory during PMM initialization.
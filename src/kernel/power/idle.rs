@@ -0,0 +1,303 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! CPU Idle Governor
+//!
+//! Every CPU's idle loop calls [`cpu_idle`], which picks an idle state,
+//! sleeps in it until an interrupt (or a write to the CPU's park word)
+//! wakes it, and records how long it slept.
+//!
+//! # States
+//!
+//! State 0 is always `C1`: WFI on arm64, HLT on x86. On x86 with
+//! MONITOR/MWAIT, `C1` uses MWAIT instead and the FADT's C2 and C3 exit
+//! latencies, where supported, add `C2` and `C3` as MWAIT hints 0x10 and
+//! 0x20. arm64 has no deeper states yet; PSCI `CPU_SUSPEND` needs power
+//! state parameters the device tree doesn't pass us.
+//!
+//! # Governor
+//!
+//! A state is only worth entering if the CPU will stay idle for its target
+//! residency (three times its exit latency). The governor predicts the
+//! idle period as the lesser of the time to the next timer and a moving
+//! average of recent idle periods, and picks the deepest state that fits
+//! the prediction and the latency limit (`kernel.cpuidle.latency_us`,
+//! unlimited by default).
+
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use crate::kernel::cmdline;
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::power::lifecycle;
+use crate::kernel::timer;
+
+use crate::log_info;
+
+/// Most idle states a platform can describe
+pub const MAX_IDLE_STATES: usize = 4;
+
+/// How a state is entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleKind {
+    /// WFI on arm64, HLT on x86
+    Halt,
+
+    /// MWAIT with the given hint, on the CPU's park word
+    Mwait(u32),
+}
+
+/// An idle state
+#[derive(Debug, Clone, Copy)]
+pub struct IdleState {
+    /// Name shown by the `cpu` command
+    pub name: &'static str,
+
+    /// How the state is entered
+    pub kind: IdleKind,
+
+    /// Worst-case time to wake, in nanoseconds
+    pub exit_latency_ns: u64,
+
+    /// Shortest idle period that pays for entering, in nanoseconds
+    pub target_residency_ns: u64,
+}
+
+impl IdleState {
+    const fn new(name: &'static str, kind: IdleKind, exit_latency_ns: u64) -> Self {
+        Self {
+            name,
+            kind,
+            exit_latency_ns,
+            target_residency_ns: exit_latency_ns * 3,
+        }
+    }
+}
+
+/// Shallowest state, always available
+const C1_HALT: IdleState = IdleState::new("C1", IdleKind::Halt, 1_000);
+
+/// States in order of depth, fixed by `init`
+static mut STATES: [IdleState; MAX_IDLE_STATES] = [C1_HALT; MAX_IDLE_STATES];
+
+/// Number of valid entries in `STATES`
+static STATE_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+/// Deepest exit latency the governor may choose, in nanoseconds
+static LATENCY_LIMIT_NS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// One CPU's idle statistics
+struct CpuIdle {
+    /// Moving average of idle periods, in nanoseconds
+    predicted_ns: AtomicU64,
+
+    /// Times each state was entered
+    usage: [AtomicU64; MAX_IDLE_STATES],
+
+    /// Time spent in each state, in nanoseconds
+    residency_ns: [AtomicU64; MAX_IDLE_STATES],
+}
+
+impl CpuIdle {
+    const fn new() -> Self {
+        Self {
+            predicted_ns: AtomicU64::new(0),
+            usage: [const { AtomicU64::new(0) }; MAX_IDLE_STATES],
+            residency_ns: [const { AtomicU64::new(0) }; MAX_IDLE_STATES],
+        }
+    }
+}
+
+static CPU_IDLE: [CpuIdle; SMP_MAX_CPUS] = [const { CpuIdle::new() }; SMP_MAX_CPUS];
+
+/// Usage of one idle state on one CPU
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdleUsage {
+    /// Times the state was entered
+    pub usage: u64,
+
+    /// Time spent in the state, in nanoseconds
+    pub residency_ns: u64,
+}
+
+/// Build the state table
+///
+/// Must run before secondary CPUs reach their idle loops.
+pub fn init() {
+    let mut count = 1;
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        use crate::kernel::arch::amd64::ops;
+
+        if ops::x86_has_mwait() {
+            unsafe {
+                STATES[0].kind = IdleKind::Mwait(0x00);
+            }
+
+            if let Some(fadt) = crate::kernel::dev::acpi::fadt() {
+                let deeper = [
+                    ("C2", 0x10, fadt.c2_latency_ns()),
+                    ("C3", 0x20, fadt.c3_latency_ns()),
+                ];
+                for (name, hint, latency) in deeper {
+                    if let Some(latency) = latency {
+                        unsafe {
+                            STATES[count] = IdleState::new(name, IdleKind::Mwait(hint), latency);
+                        }
+                        count += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    STATE_COUNT.store(count, Ordering::Release);
+
+    let limit_us = cmdline::cmdline_get_uint64("kernel.cpuidle.latency_us", u64::MAX);
+    LATENCY_LIMIT_NS.store(limit_us.saturating_mul(1000), Ordering::Relaxed);
+
+    log_info!("cpuidle: {} states", count);
+    for state in states() {
+        log_info!(
+            "  {}: {:?} exit latency {} ns",
+            state.name,
+            state.kind,
+            state.exit_latency_ns
+        );
+    }
+}
+
+/// The idle states, shallowest first
+pub fn states() -> &'static [IdleState] {
+    let count = STATE_COUNT.load(Ordering::Acquire);
+    unsafe { &(&*core::ptr::addr_of!(STATES))[..count] }
+}
+
+/// Deepest state worth entering for an idle period of `predicted_ns`
+/// without waking later than `latency_limit_ns`
+pub fn select(states: &[IdleState], predicted_ns: u64, latency_limit_ns: u64) -> usize {
+    states
+        .iter()
+        .rposition(|state| {
+            state.target_residency_ns <= predicted_ns && state.exit_latency_ns <= latency_limit_ns
+        })
+        .unwrap_or(0)
+}
+
+/// Fold a measured idle period into the prediction
+fn update_prediction(predicted_ns: u64, measured_ns: u64) -> u64 {
+    (predicted_ns * 7 + measured_ns) / 8
+}
+
+/// Index of the deepest state
+pub fn deepest() -> usize {
+    states().len() - 1
+}
+
+/// Sleep in state `index` until an interrupt or a write to `monitor`
+pub fn enter(index: usize, monitor: &AtomicU32) {
+    match states()[index].kind {
+        IdleKind::Halt => {
+            #[cfg(target_arch = "aarch64")]
+            unsafe {
+                core::arch::asm!("wfi", options(nomem, nostack));
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            crate::kernel::arch::amd64::ops::x86_idle();
+
+            #[cfg(target_arch = "riscv64")]
+            core::hint::spin_loop();
+
+            let _ = monitor;
+        }
+        IdleKind::Mwait(hint) => {
+            #[cfg(target_arch = "x86_64")]
+            unsafe {
+                crate::kernel::arch::amd64::ops::x86_mwait(monitor as *const AtomicU32, 0, hint);
+            }
+
+            #[cfg(not(target_arch = "x86_64"))]
+            let _ = (monitor, hint);
+        }
+    }
+}
+
+/// Idle the calling CPU once
+///
+/// Called in a loop by the idle thread. Parks the CPU instead if it is
+/// being taken offline.
+pub fn cpu_idle() {
+    let cpu = percpu::current_cpu_num() as usize;
+    if cpu >= SMP_MAX_CPUS {
+        return;
+    }
+
+    if lifecycle::park_requested(cpu as u32) {
+        lifecycle::park_current();
+        return;
+    }
+
+    let stats = &CPU_IDLE[cpu];
+    let start = timer::current_time();
+    let until_timer = timer::next_deadline().saturating_sub(start);
+    let predicted = stats.predicted_ns.load(Ordering::Relaxed).min(until_timer);
+
+    let index = select(states(), predicted, LATENCY_LIMIT_NS.load(Ordering::Relaxed));
+    enter(index, lifecycle::park_word(cpu as u32));
+
+    let slept = timer::current_time().saturating_sub(start);
+    stats.usage[index].fetch_add(1, Ordering::Relaxed);
+    stats.residency_ns[index].fetch_add(slept, Ordering::Relaxed);
+    stats.predicted_ns.store(
+        update_prediction(stats.predicted_ns.load(Ordering::Relaxed), slept),
+        Ordering::Relaxed,
+    );
+}
+
+/// Usage of state `index` on `cpu`
+pub fn usage(cpu: u32, index: usize) -> IdleUsage {
+    let stats = &CPU_IDLE[cpu as usize];
+    IdleUsage {
+        usage: stats.usage[index].load(Ordering::Relaxed),
+        residency_ns: stats.residency_ns[index].load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: [IdleState; 3] = [
+        C1_HALT,
+        IdleState::new("C2", IdleKind::Mwait(0x10), 20_000),
+        IdleState::new("C3", IdleKind::Mwait(0x20), 200_000),
+    ];
+
+    #[test]
+    fn test_select() {
+        assert_eq!(select(&TABLE, 0, u64::MAX), 0);
+        assert_eq!(select(&TABLE, 59_999, u64::MAX), 0);
+        assert_eq!(select(&TABLE, 60_000, u64::MAX), 1);
+        assert_eq!(select(&TABLE, 10_000_000, u64::MAX), 2);
+
+        // The latency limit caps the depth
+        assert_eq!(select(&TABLE, 10_000_000, 50_000), 1);
+        assert_eq!(select(&TABLE, 10_000_000, 0), 0);
+    }
+
+    #[test]
+    fn test_prediction() {
+        assert_eq!(update_prediction(0, 800), 100);
+        assert_eq!(update_prediction(800, 800), 800);
+
+        let mut predicted = 0;
+        for _ in 0..64 {
+            predicted = update_prediction(predicted, 1_000_000);
+        }
+        assert!(predicted > 990_000);
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! CPU Lifecycle
+//!
+//! Takes CPUs offline and brings them back online at runtime.
+//!
+//! # Offline
+//!
+//! [`cpu_offline`] runs on any CPU:
+//!
+//! 1. The target stops taking reschedule IPIs and
+//!    [`mp_unplug_cpu_mask`](crate::kernel::mp::mp_unplug_cpu_mask) shuts
+//!    down its DPCs, moves its threads to other CPUs and clears it from the
+//!    online mask, so the scheduler gives it nothing new.
+//! 2. Its park word is set and an IPI wakes it. Its idle loop sees the
+//!    word and parks: on arm64 with PSCI `CPU_OFF`, on x86 in its deepest
+//!    idle state waiting on the park word.
+//!
+//! The CPU's per-CPU state reads `Halted` once it is parked.
+//!
+//! # Online
+//!
+//! [`cpu_online`] restarts a CPU that PSCI powered off with `CPU_ON` at
+//! the kernel entry point; any other parked CPU is woken by clearing its
+//! park word, and returns to its idle loop.
+//!
+//! The boot CPU never goes offline: it owns the platform timer.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::kernel::mp::{self, cpu_num_to_mask, MpIpiTarget, MpIpiType};
+use crate::kernel::percpu::{self, CpuState, BOOT_CPU_ID, SMP_MAX_CPUS};
+use crate::kernel::power::idle;
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+
+use crate::{log_info, log_warn};
+
+/// Nonzero while a CPU should be parked; monitored by MWAIT while parked
+static PARK: [AtomicU32; SMP_MAX_CPUS] = [const { AtomicU32::new(0) }; SMP_MAX_CPUS];
+
+/// Set while a CPU is powered off by PSCI
+static POWERED_OFF: [AtomicBool; SMP_MAX_CPUS] = [const { AtomicBool::new(false) }; SMP_MAX_CPUS];
+
+/// Mark every CPU that came up as online
+pub fn init() {
+    for cpu in 0..percpu::num_cpus() {
+        mp::mp_set_cpu_online(cpu, true);
+        mp::mp_set_cpu_active(cpu, true);
+        unsafe { percpu::get_percpu(cpu as usize) }.set_state(CpuState::Running);
+    }
+
+    log_info!("cpu lifecycle: {} CPUs online", percpu::num_cpus());
+}
+
+/// Check that `cpu` exists
+fn check_cpu(cpu: u32) -> Result {
+    if cpu >= percpu::num_cpus() {
+        return Err(RX_ERR_NOT_FOUND);
+    }
+    Ok(())
+}
+
+/// State of `cpu`
+pub fn cpu_state(cpu: u32) -> CpuState {
+    unsafe { percpu::get_percpu(cpu as usize) }.state()
+}
+
+/// Whether `cpu` has been asked to park
+pub fn park_requested(cpu: u32) -> bool {
+    PARK[cpu as usize].load(Ordering::Acquire) != 0
+}
+
+/// Word an idle `cpu` waits on, written to park or unpark it
+pub fn park_word(cpu: u32) -> &'static AtomicU32 {
+    &PARK[cpu as usize]
+}
+
+/// Take `cpu` offline
+///
+/// Returns once the CPU has no threads and has been asked to park; it
+/// parks the next time its idle loop runs.
+///
+/// # Errors
+///
+/// - `RX_ERR_NOT_FOUND` - no such CPU
+/// - `RX_ERR_INVALID_ARGS` - `cpu` is the boot CPU
+/// - `RX_ERR_BAD_STATE` - `cpu` is not online, or is the last one online
+pub fn cpu_offline(cpu: u32) -> Result {
+    check_cpu(cpu)?;
+
+    if cpu == BOOT_CPU_ID {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    let online = mp::mp_get_online_mask();
+    if online & cpu_num_to_mask(cpu) == 0 || online == cpu_num_to_mask(cpu) {
+        return Err(RX_ERR_BAD_STATE);
+    }
+
+    // Stop reschedule IPIs, then drain it
+    mp::mp_set_cpu_active(cpu, false);
+    if let Err(err) = mp::mp_unplug_cpu_mask(cpu_num_to_mask(cpu)) {
+        mp::mp_set_cpu_active(cpu, true);
+        return Err(err);
+    }
+
+    // Wake it so its idle loop parks it
+    PARK[cpu as usize].store(1, Ordering::Release);
+    mp::mp_send_ipi(MpIpiTarget::Mask, cpu_num_to_mask(cpu), MpIpiType::Interrupt);
+
+    log_info!("cpu {}: going offline", cpu);
+    Ok(())
+}
+
+/// Bring `cpu` back online
+///
+/// # Errors
+///
+/// - `RX_ERR_NOT_FOUND` - no such CPU
+/// - `RX_ERR_BAD_STATE` - `cpu` was not taken offline
+/// - `RX_ERR_INTERNAL` - firmware refused to power the CPU on
+pub fn cpu_online(cpu: u32) -> Result {
+    check_cpu(cpu)?;
+
+    if mp::mp_is_cpu_online(cpu) || !park_requested(cpu) {
+        return Err(RX_ERR_BAD_STATE);
+    }
+
+    if POWERED_OFF[cpu as usize].load(Ordering::Acquire) {
+        power_on(cpu)?;
+        mp::mp_set_cpu_online(cpu, true);
+        mp::mp_set_cpu_active(cpu, true);
+        unsafe { percpu::get_percpu(cpu as usize) }.set_state(CpuState::Running);
+    } else {
+        // The write ends its MWAIT; the IPI ends a HLT or WFI
+        PARK[cpu as usize].store(0, Ordering::Release);
+        mp::mp_send_ipi(MpIpiTarget::Mask, cpu_num_to_mask(cpu), MpIpiType::Interrupt);
+    }
+
+    log_info!("cpu {}: coming online", cpu);
+    Ok(())
+}
+
+/// Restart a CPU that PSCI powered off
+#[cfg(target_arch = "aarch64")]
+fn power_on(cpu: u32) -> Result {
+    use crate::kernel::dev::psci::{self, PsciReturn};
+
+    extern "C" {
+        static kernel_entry_paddr: u64;
+    }
+
+    let (cluster, id) = crate::arch::arm64::mp::arch_cpu_num_to_cluster_cpu(cpu);
+
+    // Clear first so the restarted CPU doesn't park again
+    PARK[cpu as usize].store(0, Ordering::Release);
+    POWERED_OFF[cpu as usize].store(false, Ordering::Release);
+
+    let entry = unsafe { kernel_entry_paddr };
+    match psci::psci_cpu_on(cluster as u64, id as u64, entry as _) {
+        PsciReturn::Success | PsciReturn::AlreadyOn | PsciReturn::OnPending => Ok(()),
+        ret => {
+            log_warn!("cpu {}: PSCI CPU_ON failed: {:?}", cpu, ret);
+            PARK[cpu as usize].store(1, Ordering::Release);
+            POWERED_OFF[cpu as usize].store(true, Ordering::Release);
+            Err(RX_ERR_INTERNAL)
+        }
+    }
+}
+
+/// Only arm64 powers CPUs off
+#[cfg(not(target_arch = "aarch64"))]
+fn power_on(_cpu: u32) -> Result {
+    Err(RX_ERR_NOT_SUPPORTED)
+}
+
+/// Park the calling CPU until `cpu_online` wakes it
+///
+/// Called from the idle loop once the CPU's park word is set.
+pub fn park_current() {
+    let cpu = percpu::current_cpu_num();
+    let percpu = unsafe { percpu::get_percpu(cpu as usize) };
+    percpu.set_state(CpuState::Halted);
+
+    log_info!("cpu {}: parked", cpu);
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        // Only returns if the firmware refused
+        POWERED_OFF[cpu as usize].store(true, Ordering::Release);
+        let ret = crate::kernel::dev::psci::psci_cpu_off();
+        POWERED_OFF[cpu as usize].store(false, Ordering::Release);
+
+        log_warn!("cpu {}: PSCI CPU_OFF failed ({:?}), parking in WFI", cpu, ret);
+    }
+
+    while park_requested(cpu) {
+        idle::enter(idle::deepest(), park_word(cpu));
+    }
+
+    mp::mp_set_curr_cpu_online(true);
+    mp::mp_set_curr_cpu_active(true);
+    percpu.set_state(CpuState::Running);
+
+    log_info!("cpu {}: back online", cpu);
+}
@@ -0,0 +1,116 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Power Management
+//!
+//! Taking CPUs offline and online, choosing how deeply idle CPUs sleep, and
+//! suspending the whole system.
+//!
+//! - [`lifecycle`] - offline a CPU (drain it, park it with PSCI `CPU_OFF`
+//!   or MWAIT) and bring it back
+//! - [`idle`] - the idle governor run by every CPU's idle loop
+//! - [`suspend`] - suspend-to-idle with driver callbacks
+//!
+//! # Shell Commands
+//!
+//! `cpu` lists CPUs with their state and time in each idle state;
+//! `cpu offline <n>` and `cpu online <n>` toggle one for power testing.
+//!
+//! `suspend <ms>` suspends the system for up to `ms` milliseconds.
+
+pub mod idle;
+pub mod lifecycle;
+pub mod suspend;
+
+/// Initialize power management
+///
+/// Runs after secondary CPUs are started and before they idle.
+pub fn init() {
+    idle::init();
+    lifecycle::init();
+}
+
+/// ============================================================================
+/// Shell Command
+/// ============================================================================
+
+fn cmd_cpu(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    if argc >= 3 {
+        let cpu = match argv[2].parse::<u32>() {
+            Ok(cpu) => cpu,
+            Err(_) => {
+                crate::println!("bad cpu number '{}'", argv[2]);
+                return -1;
+            }
+        };
+        let result = match argv[1] {
+            "offline" => lifecycle::cpu_offline(cpu),
+            "online" => lifecycle::cpu_online(cpu),
+            _ => {
+                crate::println!("usage: cpu [online|offline <n>]");
+                return -1;
+            }
+        };
+        return match result {
+            Ok(()) => 0,
+            Err(err) => {
+                crate::println!("cpu {} {}: error {}", argv[1], cpu, err);
+                err
+            }
+        };
+    }
+
+    let states = idle::states();
+    crate::print!("{:>4} {:<10}", "cpu", "state");
+    for state in states {
+        crate::print!(" {:>8} {:>10}", state.name, "ms");
+    }
+    crate::println!();
+
+    for cpu in 0..crate::kernel::percpu::num_cpus() {
+        crate::print!("{:>4} {:<10}", cpu, alloc::format!("{:?}", lifecycle::cpu_state(cpu)));
+        for index in 0..states.len() {
+            let usage = idle::usage(cpu, index);
+            crate::print!(" {:>8} {:>10}", usage.usage, usage.residency_ns / 1_000_000);
+        }
+        crate::println!();
+    }
+    0
+}
+
+crate::static_command!("cpu", "list CPUs, or cpu online|offline <n>", cmd_cpu);
+
+fn cmd_suspend(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    if argc < 2 {
+        crate::println!("usage: suspend <ms>");
+        return -1;
+    }
+    let ms = match argv[1].parse::<u64>() {
+        Ok(ms) if ms > 0 => ms,
+        _ => {
+            crate::println!("bad time '{}'", argv[1]);
+            return -1;
+        }
+    };
+
+    match suspend::system_suspend(Some(ms.saturating_mul(1_000_000))) {
+        Ok(slept) => {
+            crate::println!(
+                "slept {} ms, {} ms suspended since boot, {} suspends",
+                slept / 1_000_000,
+                crate::kernel::timer::suspended_time() / 1_000_000,
+                suspend::suspend_count()
+            );
+            0
+        }
+        Err(err) => {
+            crate::println!("suspend: error {}", err);
+            err
+        }
+    }
+}
+
+crate::static_command!("suspend", "suspend the system for <ms>", cmd_suspend);
@@ -0,0 +1,229 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! System Suspend
+//!
+//! [`system_suspend`] puts the whole system to sleep until a wake event:
+//!
+//! 1. **Freeze**: every user thread is frozen. Frozen threads stay on the
+//!    run queue and can be woken, but no CPU runs them.
+//! 2. **Quiesce devices**: each driver's [`PmOps::suspend`] runs, in
+//!    reverse name order. If one fails, the drivers already suspended are
+//!    resumed, threads are thawed and the suspend is abandoned.
+//! 3. **Sleep**: suspend-to-idle. The calling CPU waits in its deepest
+//!    idle state until the wake deadline passes or a driver calls [`wake`]
+//!    from its interrupt handler; the platform tick wakes it to check the
+//!    deadline. Other CPUs have nothing to run and idle in their own loops.
+//! 4. **Resume**: the clock is re-synchronized with
+//!    [`timer::resume`](crate::kernel::timer::resume), so monotonic time
+//!    counts the sleep and timers that expired fire, then drivers resume in
+//!    name order and threads are thawed.
+//!
+//! PSCI `SYSTEM_SUSPEND` isn't used: it resumes at a physical entry point
+//! with the MMU off, and arm64 has no warm-boot path to restore the kernel
+//! from there, so every architecture suspends to idle.
+//!
+//! # Drivers
+//!
+//! A driver registers its callbacks with
+//! [`static_pm_ops!`](crate::static_pm_ops):
+//!
+//! ```ignore
+//! fn uart_suspend() -> Result { ... }
+//! fn uart_resume() { ... }
+//!
+//! crate::static_pm_ops!("uart", uart_suspend, uart_resume);
+//! ```
+//!
+//! The macro places a [`PmOps`] in a `kpm.<name>` section, which the linker
+//! script collects, sorted by name, between `kpm_begin` and `kpm_end`.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use crate::kernel::power::idle;
+use crate::kernel::sched;
+use crate::kernel::timer;
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+
+use crate::{log_info, log_warn};
+
+/// A driver's suspend and resume callbacks
+pub struct PmOps {
+    /// Driver name, which also orders the callbacks
+    pub name: &'static str,
+
+    /// Stop the device; it may stay armed as a wake source
+    pub suspend: fn() -> Result,
+
+    /// Restart the device after `suspend` succeeded
+    pub resume: fn(),
+}
+
+impl PmOps {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, suspend: fn() -> Result, resume: fn()) -> Self {
+        Self { name, suspend, resume }
+    }
+}
+
+extern "C" {
+    static kpm_begin: PmOps;
+    static kpm_end: PmOps;
+}
+
+/// Every registered driver, sorted by name
+pub fn pm_ops() -> &'static [PmOps] {
+    unsafe {
+        let begin = core::ptr::addr_of!(kpm_begin);
+        let end = core::ptr::addr_of!(kpm_end);
+        let len = (end as usize - begin as usize) / core::mem::size_of::<PmOps>();
+        core::slice::from_raw_parts(begin, len)
+    }
+}
+
+/// Set while a suspend is in progress
+static SUSPENDING: AtomicBool = AtomicBool::new(false);
+
+/// Nonzero once a wake event arrives; monitored by MWAIT while asleep
+static WAKE: AtomicU32 = AtomicU32::new(0);
+
+/// Times the system has suspended
+static SUSPEND_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Wake the system from suspend
+///
+/// Called by drivers from wake-source interrupt handlers. Does nothing if
+/// the system isn't suspended.
+pub fn wake() {
+    if SUSPENDING.load(Ordering::Acquire) {
+        WAKE.store(1, Ordering::Release);
+    }
+}
+
+/// Times the system has suspended
+pub fn suspend_count() -> u64 {
+    SUSPEND_COUNT.load(Ordering::Relaxed)
+}
+
+/// Suspend every driver in `ops`, last first
+///
+/// If one fails, the ones already suspended are resumed and its error is
+/// returned.
+fn suspend_devices(ops: &[PmOps]) -> Result {
+    for (index, op) in ops.iter().enumerate().rev() {
+        if let Err(err) = (op.suspend)() {
+            log_warn!("suspend: {} failed to suspend: {}", op.name, err);
+            resume_devices(&ops[index + 1..]);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Resume every driver in `ops`, in order
+fn resume_devices(ops: &[PmOps]) {
+    for op in ops {
+        (op.resume)();
+    }
+}
+
+/// Whether the sleep should end at `now`
+fn should_wake(now: u64, wake_at: u64, woken: bool) -> bool {
+    woken || now >= wake_at
+}
+
+/// Suspend to idle until a wake event or `wake_after` nanoseconds pass
+///
+/// With `wake_after` of `None` only a driver's [`wake`] ends the sleep.
+/// Returns how long the system slept, in nanoseconds.
+///
+/// # Errors
+///
+/// - `RX_ERR_BAD_STATE` - a suspend is already in progress
+/// - any error from a driver's suspend callback, which aborts the suspend
+pub fn system_suspend(wake_after: Option<u64>) -> Result<u64> {
+    if SUSPENDING.swap(true, Ordering::AcqRel) {
+        return Err(RX_ERR_BAD_STATE);
+    }
+    WAKE.store(0, Ordering::Release);
+
+    let frozen = sched::freeze_user_threads();
+    log_info!("suspend: froze {} threads", frozen);
+
+    let ops = pm_ops();
+    if let Err(err) = suspend_devices(ops) {
+        sched::thaw_threads();
+        SUSPENDING.store(false, Ordering::Release);
+        return Err(err);
+    }
+
+    let start = timer::current_time();
+    let wake_at = wake_after.map_or(u64::MAX, |ns| start.saturating_add(ns));
+    log_info!("suspend: {} devices suspended, sleeping", ops.len());
+
+    while !should_wake(timer::current_time(), wake_at, WAKE.load(Ordering::Acquire) != 0) {
+        idle::enter(idle::deepest(), &WAKE);
+    }
+    let slept = timer::current_time().saturating_sub(start);
+
+    timer::resume(start, slept);
+    resume_devices(ops);
+    let thawed = sched::thaw_threads();
+
+    SUSPEND_COUNT.fetch_add(1, Ordering::Relaxed);
+    SUSPENDING.store(false, Ordering::Release);
+
+    log_info!("suspend: resumed after {} ms, thawed {} threads", slept / 1_000_000, thawed);
+    Ok(slept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    static SUSPENDED: AtomicUsize = AtomicUsize::new(0);
+
+    fn ok_suspend() -> Result {
+        SUSPENDED.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn failing_suspend() -> Result {
+        Err(RX_ERR_BAD_STATE)
+    }
+
+    fn counting_resume() {
+        SUSPENDED.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_suspend_rollback() {
+        let ops = [
+            PmOps::new("a", failing_suspend, counting_resume),
+            PmOps::new("b", ok_suspend, counting_resume),
+            PmOps::new("c", ok_suspend, counting_resume),
+        ];
+
+        // "c" and "b" suspend, "a" fails and both are resumed
+        assert_eq!(suspend_devices(&ops), Err(RX_ERR_BAD_STATE));
+        assert_eq!(SUSPENDED.load(Ordering::Relaxed), 0);
+
+        assert_eq!(suspend_devices(&ops[1..]), Ok(()));
+        assert_eq!(SUSPENDED.load(Ordering::Relaxed), 2);
+        resume_devices(&ops[1..]);
+        assert_eq!(SUSPENDED.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_should_wake() {
+        assert!(!should_wake(100, 200, false));
+        assert!(should_wake(200, 200, false));
+        assert!(should_wake(100, 200, true));
+        assert!(!should_wake(100, u64::MAX, false));
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Preemption Control
+//!
+//! Per-CPU preemption-disable depth, and the checks that catch a thread
+//! blocking or scheduling when it must not.
+//!
+//! # Design
+//!
+//! - **Depth**: Each CPU counts nested [`disable`] calls
+//!   (`PerCpu::preempt_depth`). Holding a
+//!   [`SpinMutex`](crate::kernel::sync::SpinMutex) counts as one level.
+//! - **Deferred preemption**: An interrupt that would preempt a thread with
+//!   preemption disabled leaves `PerCpu::preempt_deferred` set instead (see
+//!   [`crate::kernel::irq::exit`]). The [`enable`] that brings the depth
+//!   back to 0 takes the preemption.
+//! - **Atomic context**: A CPU with preemption disabled or inside an
+//!   interrupt handler is atomic. Blocking APIs call [`might_block`] and
+//!   the scheduling entry points call [`check_schedule`]. Either one in
+//!   atomic context is a bug: it is counted, logged with the caller, and
+//!   panics in debug builds.
+//!
+//! Until per-CPU data is set up there is a single CPU and nothing to
+//! track, so all of this is a no-op.
+//!
+//! # Usage
+//!
+//! ```rust
+//! {
+//!     let _no_preempt = PreemptDisabled::new();
+//!     // This thread stays on this CPU until the guard drops
+//! }
+//! ```
+
+use crate::kernel::percpu::{self, PerCpu, SMP_MAX_CPUS};
+use crate::kernel::thread;
+use core::marker::PhantomData;
+use core::panic::Location;
+use core::sync::atomic::Ordering;
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+// Import logging macros
+use crate::log_error;
+
+/// ============================================================================
+/// Counters
+/// ============================================================================
+
+KCOUNTER!(PREEMPT_DEFERRED, "kernel.preempt.deferred");
+KCOUNTER!(PREEMPT_ATOMIC_BLOCK, "kernel.preempt.atomic_block");
+KCOUNTER!(PREEMPT_ATOMIC_SCHEDULE, "kernel.preempt.atomic_schedule");
+
+/// ============================================================================
+/// Depth
+/// ============================================================================
+
+/// This CPU's per-CPU data, or `None` before it is set up
+fn local() -> Option<&'static PerCpu> {
+    if percpu::num_cpus() == 0 {
+        return None;
+    }
+    let cpu = (percpu::current_cpu_num() as usize).min(SMP_MAX_CPUS - 1);
+    Some(unsafe { percpu::get_percpu(cpu) })
+}
+
+/// Raise `cpu`'s depth, returning the new depth
+fn disable_on(cpu: &PerCpu) -> u32 {
+    cpu.preempt_depth.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Lower `cpu`'s depth
+///
+/// Returns whether a deferred preemption is now due: only when the depth
+/// reaches 0 outside interrupt context.
+fn enable_on(cpu: &PerCpu) -> bool {
+    let depth = cpu.preempt_depth.fetch_sub(1, Ordering::Relaxed);
+    debug_assert!(depth > 0, "preemption enabled more often than disabled");
+    depth == 1 && cpu.irq_depth.load(Ordering::Relaxed) == 0 && cpu.preempt_deferred.swap(false, Ordering::Relaxed)
+}
+
+fn is_atomic_on(cpu: &PerCpu) -> bool {
+    cpu.preempt_depth.load(Ordering::Relaxed) > 0 || cpu.irq_depth.load(Ordering::Relaxed) > 0
+}
+
+/// Hold off a preemption of `cpu` while its preemption is disabled
+///
+/// Called by [`irq::exit`](crate::kernel::irq::exit) before reporting a
+/// preemption; returns true if it was deferred.
+pub fn defer_on(cpu: &PerCpu) -> bool {
+    if cpu.preempt_depth.load(Ordering::Relaxed) == 0 {
+        return false;
+    }
+    cpu.preempt_deferred.store(true, Ordering::Relaxed);
+    PREEMPT_DEFERRED.add(1);
+    true
+}
+
+/// Disable preemption on this CPU
+///
+/// Nests; each call needs a matching [`enable`].
+#[inline]
+pub fn disable() {
+    if let Some(cpu) = local() {
+        disable_on(cpu);
+    }
+}
+
+/// Re-enable preemption on this CPU
+///
+/// The outermost call takes any preemption deferred meanwhile.
+#[inline]
+pub fn enable() {
+    if local().map_or(false, enable_on) {
+        thread::thread_preempt();
+    }
+}
+
+/// This CPU's preemption-disable depth
+pub fn depth() -> u32 {
+    local().map_or(0, |cpu| cpu.preempt_depth.load(Ordering::Relaxed))
+}
+
+/// Whether this CPU has preemption disabled or is in an interrupt handler
+pub fn in_atomic() -> bool {
+    local().map_or(false, is_atomic_on)
+}
+
+/// Preemption disabled for as long as it lives
+pub struct PreemptDisabled {
+    /// Must be dropped on the CPU it was made on
+    _not_send: PhantomData<*const ()>,
+}
+
+impl PreemptDisabled {
+    /// Disable preemption on this CPU
+    pub fn new() -> Self {
+        disable();
+        Self { _not_send: PhantomData }
+    }
+}
+
+impl Default for PreemptDisabled {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PreemptDisabled {
+    fn drop(&mut self) {
+        enable();
+    }
+}
+
+/// ============================================================================
+/// Atomic Context Checks
+/// ============================================================================
+
+/// Note that the caller may block
+///
+/// Called on entry to every blocking API, whether or not it ends up
+/// blocking this time, so a caller that only blocks under load is caught
+/// early.
+#[track_caller]
+#[inline]
+pub fn might_block() {
+    if let Some(cpu) = local() {
+        if is_atomic_on(cpu) {
+            PREEMPT_ATOMIC_BLOCK.add(1);
+            atomic_violation("blocking", cpu, Location::caller());
+        }
+    }
+}
+
+/// Note that the caller is about to switch threads
+#[track_caller]
+#[inline]
+pub fn check_schedule() {
+    if let Some(cpu) = local() {
+        if is_atomic_on(cpu) {
+            PREEMPT_ATOMIC_SCHEDULE.add(1);
+            atomic_violation("scheduling", cpu, Location::caller());
+        }
+    }
+}
+
+#[cold]
+fn atomic_violation(what: &str, cpu: &PerCpu, caller: &Location<'_>) {
+    let preempt_depth = cpu.preempt_depth.load(Ordering::Relaxed);
+    let irq_depth = cpu.irq_depth.load(Ordering::Relaxed);
+    if cfg!(debug_assertions) {
+        panic!(
+            "{} while atomic at {} (preempt depth {}, irq depth {})",
+            what, caller, preempt_depth, irq_depth
+        );
+    }
+    log_error!(
+        "{} while atomic at {} (preempt depth {}, irq depth {})",
+        what,
+        caller,
+        preempt_depth,
+        irq_depth
+    );
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting_depth() {
+        let cpu = PerCpu::zeroed();
+        assert!(!is_atomic_on(&cpu));
+        assert_eq!(disable_on(&cpu), 1);
+        assert_eq!(disable_on(&cpu), 2);
+        assert!(is_atomic_on(&cpu));
+        assert!(!enable_on(&cpu));
+        assert!(!enable_on(&cpu));
+        assert!(!is_atomic_on(&cpu));
+    }
+
+    #[test]
+    fn test_deferred_to_outermost_enable() {
+        let cpu = PerCpu::zeroed();
+
+        // Nothing to defer with preemption enabled
+        assert!(!defer_on(&cpu));
+
+        disable_on(&cpu);
+        disable_on(&cpu);
+        assert!(defer_on(&cpu));
+        assert!(!enable_on(&cpu));
+        assert!(enable_on(&cpu));
+        assert!(!cpu.preempt_deferred.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_deferred_not_taken_in_irq() {
+        let cpu = PerCpu::zeroed();
+        disable_on(&cpu);
+        defer_on(&cpu);
+
+        // A handler dropping the last level leaves it for later
+        cpu.irq_depth.store(1, Ordering::Relaxed);
+        assert!(!enable_on(&cpu));
+        assert!(cpu.preempt_deferred.load(Ordering::Relaxed));
+        assert!(is_atomic_on(&cpu));
+    }
+}
@@ -37,16 +37,23 @@
 //! let aspace = process.address_space();
 //! ```
 
+pub mod stack;
 
 use crate::kernel::vm::aspace::*;
 use crate::kernel::vm::Result;
 use crate::rustux::types::*;
-use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU64, Ordering};
 
 // Import logging macros
 use crate::{log_debug, log_info};
 use crate::kernel::sync::{Mutex, MutexGuard};
-use crate::kernel::sync::spin::SpinMutex;
+use crate::kernel::sync::spin::{SpinLock, SpinMutex};
+use crate::kernel::thread::{self, RuntimeTotals, TaskRuntimeInfo};
+use crate::kernel::object::koid::{alloc_koid, Koid};
+use crate::kernel::lib::slab::ObjectCache;
+#[cfg(target_arch = "x86_64")]
+use crate::kernel::arch::amd64::ioport::IoBitmap;
+use alloc::boxed::Box;
 use alloc::vec::Vec;
 
 /// ============================================================================
@@ -253,12 +260,15 @@ pub enum ObjectType {
 /// Maximum handles per process
 pub const MAX_HANDLES: usize = 256;
 
+/// Cache handle entries are allocated from, shared by all processes
+static HANDLE_CACHE: ObjectCache = ObjectCache::for_type::<HandleEntry>("handle");
+
 /// Handle table
 ///
 /// Manages handles for a single process.
 pub struct HandleTable {
     /// Handle entries
-    handles: Mutex<[Option<HandleEntry>; MAX_HANDLES]>,
+    handles: Mutex<[Option<Box<HandleEntry, &'static ObjectCache>>; MAX_HANDLES]>,
 
     /// Next handle index to allocate
     next_index: core::sync::atomic::AtomicUsize,
@@ -291,12 +301,16 @@ impl HandleTable {
                 if handles[idx].is_none() {
                     let handle = ((idx + 1) as u32); // Handles start at 1
 
-                    handles[idx] = Some(HandleEntry {
+                    let entry = HandleEntry {
                         handle,
                         object_id,
                         rights,
                         object_type,
-                    });
+                    };
+                    handles[idx] = Some(
+                        Box::try_new_in(entry, &HANDLE_CACHE)
+                            .map_err(|_| crate::kernel::vm::VmError::NoMemory)?,
+                    );
 
                     self.count.fetch_add(1, Ordering::Relaxed);
                     self.next_index.store((idx + 1) % MAX_HANDLES, Ordering::Relaxed);
@@ -344,7 +358,7 @@ impl HandleTable {
         let handles = self.handles.lock();
 
         if let Some(ref entry) = handles[idx] {
-            Ok(entry.clone())
+            Ok(HandleEntry::clone(entry))
         } else {
             Err(crate::kernel::vm::VmError::NotFound)
         }
@@ -381,6 +395,9 @@ pub struct Process {
     /// Process ID
     pub pid: ProcessId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Process state
     pub state: Mutex<ProcessState>,
 
@@ -410,6 +427,16 @@ pub struct Process {
 
     /// Creation flags
     pub flags: ProcessFlags,
+
+    /// Runtime of threads that have left the process
+    pub exited_runtime: RuntimeTotals,
+
+    /// Clock the process reads as UTC (0 = the system UTC clock)
+    pub utc_clock: AtomicU64,
+
+    /// I/O ports granted by `rx_ioports_request`, None until the first
+    #[cfg(target_arch = "x86_64")]
+    pub io_bitmap: SpinMutex<Option<Box<IoBitmap>>>,
 }
 
 /// Process creation flags
@@ -443,6 +470,7 @@ impl Process {
 
         Ok(Self {
             pid,
+            koid: alloc_koid(),
             state: Mutex::new(ProcessState::Creating),
             address_space: Mutex::new(None),
             handles: HandleTable::new(),
@@ -453,6 +481,10 @@ impl Process {
             name: Mutex::new(None),
             ref_count: AtomicU64::new(1),
             flags,
+            exited_runtime: RuntimeTotals::new(),
+            utc_clock: AtomicU64::new(0),
+            #[cfg(target_arch = "x86_64")]
+            io_bitmap: SpinMutex::new(None),
         })
     }
 
@@ -461,6 +493,11 @@ impl Process {
         self.pid
     }
 
+    /// Get the kernel object ID
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
     /// Get the process state
     pub fn state(&self) -> ProcessState {
         *self.state.lock()
@@ -496,10 +533,15 @@ impl Process {
     }
 
     /// Remove a thread from the process
+    ///
+    /// The thread's runtime stays counted in the process's.
     pub fn remove_thread(&self, tid: crate::kernel::thread::ThreadId) {
         let mut threads = self.threads.lock();
         if let Some(pos) = threads.iter().position(|&t| t == tid) {
             threads.remove(pos);
+            if let Some(thread) = thread::get_thread_by_id(tid) {
+                self.exited_runtime.add(&thread.runtime());
+            }
             log_debug!("Thread removed from process: pid={} tid={}", self.pid, tid);
         }
     }
@@ -509,6 +551,18 @@ impl Process {
         self.threads.lock().len()
     }
 
+    /// CPU time, queue time and context switches of every thread the
+    /// process has had
+    pub fn runtime(&self) -> TaskRuntimeInfo {
+        let mut info = self.exited_runtime.get();
+        for &tid in self.threads.lock().iter() {
+            if let Some(thread) = thread::get_thread_by_id(tid) {
+                info.add(&thread.runtime());
+            }
+        }
+        info
+    }
+
     /// Get the parent process ID
     pub fn parent_pid(&self) -> Option<ProcessId> {
         *self.parent_pid.lock()
@@ -538,6 +592,22 @@ impl Process {
         *self.name.lock()
     }
 
+    /// Clock the process reads as UTC, if it has its own
+    pub fn utc_clock(&self) -> Option<u64> {
+        match self.utc_clock.load(Ordering::Acquire) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+
+    /// Replace the process's UTC clock, returning the previous one
+    pub fn swap_utc_clock(&self, clock: Option<u64>) -> Option<u64> {
+        match self.utc_clock.swap(clock.unwrap_or(0), Ordering::AcqRel) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+
     /// Increment reference count
     pub fn ref_inc(&self) {
         self.ref_count.fetch_add(1, Ordering::Relaxed);
@@ -581,8 +651,10 @@ pub const MAX_PROCESSES: usize = 4096;
 /// Global process table
 static mut PROCESS_TABLE: [Option<Process>; MAX_PROCESSES] = [const { None }; MAX_PROCESSES];
 
-/// Process table lock
-static PROCESS_TABLE_LOCK: AtomicBool = AtomicBool::new(false);
+/// Process table lock, held to insert or remove
+///
+/// Lookups read the table without it.
+static PROCESS_TABLE_LOCK: SpinLock<()> = SpinLock::new(());
 
 /// Initialize the process subsystem
 pub fn init() {
@@ -618,33 +690,65 @@ pub fn lookup_mut(pid: ProcessId) -> Option<&'static mut Process> {
     }
 }
 
+/// Look up a process by koid
+///
+/// Walks the whole process table, so this is for debuggers rather than
+/// hot paths.
+pub fn lookup_by_koid(koid: Koid) -> Option<&'static Process> {
+    // Racy against insert/remove, like `ps`
+    unsafe { PROCESS_TABLE.iter() }.flatten().find(|p| p.koid == koid)
+}
+
 /// Insert a process into the table
 pub fn insert(process: Process) -> Result {
     let pid = process.pid;
     let idx = (pid as usize) % MAX_PROCESSES;
 
-    // Acquire lock
-    while PROCESS_TABLE_LOCK.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-        core::hint::spin_loop();
-    }
+    {
+        let _guard = PROCESS_TABLE_LOCK.lock();
+        unsafe {
+            if PROCESS_TABLE[idx].is_some() {
+                return Err(crate::kernel::vm::VmError::Busy);
+            }
 
-    unsafe {
-        if PROCESS_TABLE[idx].is_some() {
-            PROCESS_TABLE_LOCK.store(false, Ordering::Release);
-            return Err(crate::kernel::vm::VmError::Busy);
+            PROCESS_TABLE[idx] = Some(process);
         }
-
-        PROCESS_TABLE[idx] = Some(process);
     }
 
-    // Release lock
-    PROCESS_TABLE_LOCK.store(false, Ordering::Release);
-
     log_debug!("Process inserted into table: pid={}", pid);
 
     Ok(())
 }
 
+/// `ps` console command
+fn cmd_ps(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    crate::println!(
+        "{:>6} {:>6} {:>6} {:<10} {:>7} {:>7} {:>10}  {}",
+        "pid", "ppid", "job", "state", "threads", "handles", "cpu ms", "name"
+    );
+
+    // Racy against insert/remove, which is fine for a debug listing
+    let mut count = 0;
+    for process in unsafe { PROCESS_TABLE.iter() }.flatten() {
+        crate::println!(
+            "{:>6} {:>6} {:>6} {:<10} {:>7} {:>7} {:>10}  {}",
+            process.pid(),
+            process.parent_pid().unwrap_or(0),
+            process.job_id,
+            alloc::format!("{:?}", process.state()),
+            process.thread_count(),
+            process.handles.count(),
+            process.runtime().cpu_time / 1_000_000,
+            process.name().unwrap_or("")
+        );
+        count += 1;
+    }
+    crate::println!("{} processes", count);
+    0
+}
+
+crate::static_command!("ps", "list processes", cmd_ps);
+
 /// Remove a process from the table
 pub fn remove(pid: ProcessId) -> Option<Process> {
     if pid == PID_INVALID {
@@ -653,19 +757,10 @@ pub fn remove(pid: ProcessId) -> Option<Process> {
 
     let idx = (pid as usize) % MAX_PROCESSES;
 
-    // Acquire lock
-    while PROCESS_TABLE_LOCK.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-        core::hint::spin_loop();
-    }
-
-    let result = unsafe {
+    let _guard = PROCESS_TABLE_LOCK.lock();
+    unsafe {
         core::mem::replace(&mut PROCESS_TABLE[idx], None)
-    };
-
-    // Release lock
-    PROCESS_TABLE_LOCK.store(false, Ordering::Release);
-
-    result
+    }
 }
 
 // ============================================================================
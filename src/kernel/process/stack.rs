@@ -0,0 +1,192 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Initial User Stack
+//!
+//! Lays out the System V style startup stack that `crt0::parse_auxv`
+//! walks: argc, argv, envp and the auxiliary vector at the stack
+//! pointer, with the strings and the `AT_RANDOM` bytes above them. The
+//! image is built in a kernel buffer and copied to the top of the new
+//! process's stack before its first thread starts.
+//!
+//! ```text
+//! stack_top -> +-----------------+
+//!              | strings         |
+//!              | AT_RANDOM bytes |
+//!              | padding         |
+//!              | auxv pairs      |
+//!              | NULL, envp[]    |
+//!              | NULL, argv[]    |
+//!        sp -> | argc            |  (16-byte aligned)
+//!              +-----------------+
+//! ```
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+
+/// Auxiliary vector tags (System V ABI values)
+pub mod auxv {
+    pub const AT_NULL: u64 = 0;
+    pub const AT_PHDR: u64 = 3;
+    pub const AT_PHENT: u64 = 4;
+    pub const AT_PHNUM: u64 = 5;
+    pub const AT_PAGESZ: u64 = 6;
+    pub const AT_BASE: u64 = 7;
+    pub const AT_ENTRY: u64 = 9;
+    pub const AT_RANDOM: u64 = 25;
+}
+
+/// Number of random bytes `AT_RANDOM` points at
+pub const AT_RANDOM_SIZE: usize = 16;
+
+/// Largest initial stack image accepted
+pub const MAX_IMAGE_SIZE: usize = 64 * 1024;
+
+/// A laid-out initial stack
+pub struct InitialStack {
+    /// Initial stack pointer
+    pub sp: u64,
+
+    /// Bytes from `sp` up to the stack top
+    pub image: Vec<u8>,
+}
+
+impl InitialStack {
+    /// Lay out a stack ending at `stack_top`
+    ///
+    /// `auxv` must not contain `AT_RANDOM` or `AT_NULL`; both are added,
+    /// with fresh bytes from the kernel CPRNG behind `AT_RANDOM`.
+    pub fn new(stack_top: u64, args: &[&[u8]], env: &[&[u8]], auxv: &[(u64, u64)]) -> Result<Self> {
+        let mut random = [0u8; AT_RANDOM_SIZE];
+        crate::kernel::lib::crypto::draw(&mut random);
+        Self::with_random(stack_top, args, env, auxv, &random)
+    }
+
+    /// Lay out a stack with the given `AT_RANDOM` bytes
+    fn with_random(
+        stack_top: u64,
+        args: &[&[u8]],
+        env: &[&[u8]],
+        auxv: &[(u64, u64)],
+        random: &[u8; AT_RANDOM_SIZE],
+    ) -> Result<Self> {
+        if !stack_top.is_multiple_of(16) {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        let strings: usize = args.iter().chain(env.iter()).map(|s| s.len() + 1).sum();
+        let data_size = (strings + AT_RANDOM_SIZE).next_multiple_of(16);
+
+        // argc, argv + NULL, envp + NULL, auxv with AT_RANDOM and AT_NULL
+        let words = 1 + (args.len() + 1) + (env.len() + 1) + (auxv.len() + 2) * 2;
+        let size = (data_size + words * 8).next_multiple_of(16);
+        if size > MAX_IMAGE_SIZE || size as u64 > stack_top {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+
+        let sp = stack_top - size as u64;
+        let mut image = vec![0u8; size];
+
+        // Strings fill down from the top, AT_RANDOM sits below them
+        let mut cursor = size;
+        let mut place = |bytes: &[u8], image: &mut [u8]| -> u64 {
+            cursor -= bytes.len() + 1;
+            image[cursor..cursor + bytes.len()].copy_from_slice(bytes);
+            sp + cursor as u64
+        };
+        let arg_ptrs: Vec<u64> = args.iter().map(|s| place(s, &mut image)).collect();
+        let env_ptrs: Vec<u64> = env.iter().map(|s| place(s, &mut image)).collect();
+        let random_offset = size - strings - AT_RANDOM_SIZE;
+        image[random_offset..random_offset + AT_RANDOM_SIZE].copy_from_slice(random);
+
+        let mut words_out = Vec::with_capacity(words);
+        words_out.push(args.len() as u64);
+        words_out.extend_from_slice(&arg_ptrs);
+        words_out.push(0);
+        words_out.extend_from_slice(&env_ptrs);
+        words_out.push(0);
+        for &(tag, value) in auxv {
+            words_out.extend_from_slice(&[tag, value]);
+        }
+        words_out.extend_from_slice(&[auxv::AT_RANDOM, sp + random_offset as u64]);
+        words_out.extend_from_slice(&[auxv::AT_NULL, 0]);
+
+        for (chunk, word) in image.chunks_exact_mut(8).zip(words_out.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        Ok(Self { sp, image })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(stack: &InitialStack, addr: u64) -> u64 {
+        let offset = (addr - stack.sp) as usize;
+        u64::from_le_bytes(stack.image[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn string(stack: &InitialStack, addr: u64) -> &[u8] {
+        let offset = (addr - stack.sp) as usize;
+        let len = stack.image[offset..].iter().position(|&b| b == 0).unwrap();
+        &stack.image[offset..offset + len]
+    }
+
+    #[test]
+    fn test_layout_matches_crt() {
+        let top = 0x7fff_0000_0000u64;
+        let random = [0xa5u8; AT_RANDOM_SIZE];
+        let stack = InitialStack::with_random(
+            top,
+            &[b"init", b"--verbose"],
+            &[b"HOME=/"],
+            &[(auxv::AT_PAGESZ, 4096)],
+            &random,
+        )
+        .unwrap();
+
+        assert_eq!(stack.sp % 16, 0);
+        assert_eq!(stack.sp + stack.image.len() as u64, top);
+
+        // argc, argv, envp as crt0 walks them
+        let mut p = stack.sp;
+        assert_eq!(word(&stack, p), 2);
+        p += 8;
+        assert_eq!(string(&stack, word(&stack, p)), b"init");
+        assert_eq!(string(&stack, word(&stack, p + 8)), b"--verbose");
+        assert_eq!(word(&stack, p + 16), 0);
+        p += 24;
+        assert_eq!(string(&stack, word(&stack, p)), b"HOME=/");
+        assert_eq!(word(&stack, p + 8), 0);
+        p += 16;
+
+        assert_eq!((word(&stack, p), word(&stack, p + 8)), (auxv::AT_PAGESZ, 4096));
+        assert_eq!(word(&stack, p + 16), auxv::AT_RANDOM);
+        let random_addr = word(&stack, p + 24);
+        let offset = (random_addr - stack.sp) as usize;
+        assert_eq!(&stack.image[offset..offset + AT_RANDOM_SIZE], &random);
+        assert_eq!((word(&stack, p + 32), word(&stack, p + 40)), (auxv::AT_NULL, 0));
+    }
+
+    #[test]
+    fn test_rejects_oversized_image() {
+        let big = [b'x'; MAX_IMAGE_SIZE];
+        let random = [0u8; AT_RANDOM_SIZE];
+        assert_eq!(
+            InitialStack::with_random(0x10_0000, &[&big], &[], &[], &random).err(),
+            Some(RX_ERR_OUT_OF_RANGE)
+        );
+        assert_eq!(
+            InitialStack::with_random(0x10_0008, &[], &[], &[], &random).err(),
+            Some(RX_ERR_INVALID_ARGS)
+        );
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Deadline Scheduling Class
+//!
+//! Threads given a deadline profile get `capacity` nanoseconds of CPU in
+//! every `period`, to be used within `relative_deadline` of the period's
+//! start. Runnable deadline threads always run ahead of fixed-priority
+//! threads and among themselves earliest deadline first (EDF).
+//!
+//! # Design
+//!
+//! - **Admission control**: a profile is only applied if the sum of
+//!   `capacity / period` over every deadline thread stays within
+//!   [`MAX_UTILIZATION_PPM`], so EDF can meet every deadline and the
+//!   fixed-priority threads keep the remainder of the CPU.
+//! - **Hard reservation**: a thread that uses up its capacity is throttled
+//!   until its period ends, even if the CPU is otherwise idle, so one
+//!   misbehaving thread can't eat another's reservation.
+//! - **Periods start on wakeup**: a thread that wakes after its period has
+//!   ended starts a new one at the wakeup time with a full budget; a thread
+//!   that wakes within its period keeps what is left.
+//!
+//! There is one run queue, so capacity is admitted against one CPU.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::kernel::thread::ThreadId;
+use crate::rustux::types::err::*;
+use crate::rustux::types::Status;
+
+/// Shortest capacity a profile may reserve, in nanoseconds
+pub const MIN_CAPACITY: u64 = 100_000; // 100us
+
+/// Share of the CPU deadline threads may reserve, in parts per million
+///
+/// The rest is left for fixed-priority threads.
+pub const MAX_UTILIZATION_PPM: u64 = 900_000;
+
+/// Utilization admitted so far, in parts per million
+static ADMITTED_PPM: AtomicU64 = AtomicU64::new(0);
+
+/// Deadline scheduling parameters, all in nanoseconds
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeadlineParams {
+    /// CPU time reserved per period
+    pub capacity: u64,
+
+    /// Time from the start of a period by which the capacity must be used
+    pub relative_deadline: u64,
+
+    /// Length of a period
+    pub period: u64,
+}
+
+impl DeadlineParams {
+    /// Check `MIN_CAPACITY <= capacity <= relative_deadline <= period`
+    pub fn validate(&self) -> Result<(), Status> {
+        if self.capacity < MIN_CAPACITY
+            || self.capacity > self.relative_deadline
+            || self.relative_deadline > self.period
+        {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        Ok(())
+    }
+
+    /// Share of a CPU reserved, in parts per million, rounded up
+    pub fn utilization_ppm(&self) -> u64 {
+        ((self.capacity as u128 * 1_000_000).div_ceil(self.period as u128)) as u64
+    }
+}
+
+/// Reserve the utilization of `params`
+///
+/// Fails with `RX_ERR_NO_RESOURCES` if that would overcommit the CPU.
+pub fn admit(params: &DeadlineParams) -> Result<(), Status> {
+    let ppm = params.utilization_ppm();
+    ADMITTED_PPM
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |admitted| {
+            admitted.checked_add(ppm).filter(|&total| total <= MAX_UTILIZATION_PPM)
+        })
+        .map(|_| ())
+        .map_err(|_| RX_ERR_NO_RESOURCES)
+}
+
+/// Give back the utilization reserved by `admit(params)`
+pub fn release(params: &DeadlineParams) {
+    ADMITTED_PPM.fetch_sub(params.utilization_ppm(), Ordering::AcqRel);
+}
+
+/// Utilization admitted so far, in parts per million
+pub fn admitted_ppm() -> u64 {
+    ADMITTED_PPM.load(Ordering::Relaxed)
+}
+
+/// A deadline thread's position in its current period
+#[derive(Debug, Clone, Copy)]
+pub struct DeadlineState {
+    /// Parameters from the applied profile
+    pub params: DeadlineParams,
+
+    /// Absolute deadline of the current period
+    pub deadline: u64,
+
+    /// End of the current period, when the budget is replenished
+    pub period_end: u64,
+
+    /// CPU time left in the current period
+    pub remaining: u64,
+}
+
+impl DeadlineState {
+    /// State for a thread whose first period starts at its next wakeup
+    pub const fn new(params: DeadlineParams) -> Self {
+        Self {
+            params,
+            deadline: 0,
+            period_end: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Start a new period at `now` if the current one has ended
+    pub fn replenish(&mut self, now: u64) {
+        if now >= self.period_end {
+            self.deadline = now + self.params.relative_deadline;
+            self.period_end = now + self.params.period;
+            self.remaining = self.params.capacity;
+        }
+    }
+
+    /// Charge `ns` of CPU time against the budget
+    pub fn charge(&mut self, ns: u64) {
+        self.remaining = self.remaining.saturating_sub(ns);
+    }
+
+    /// Whether the budget for this period is used up
+    pub fn is_throttled(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+/// Threads ordered by a time key: deadline for runnable threads, release
+/// time for throttled ones
+///
+/// Threads with equal keys keep insertion order.
+pub struct TimeQueue {
+    entries: Vec<(u64, ThreadId)>,
+}
+
+impl TimeQueue {
+    /// An empty queue
+    pub const fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    /// Queue `tid` at time `key`
+    pub fn insert(&mut self, key: u64, tid: ThreadId) {
+        let pos = self.entries.partition_point(|&(k, _)| k <= key);
+        self.entries.insert(pos, (key, tid));
+    }
+
+    /// Remove and return the thread with the earliest key
+    pub fn pop(&mut self) -> Option<ThreadId> {
+        if self.entries.is_empty() {
+            None
+        } else {
+            Some(self.entries.remove(0).1)
+        }
+    }
+
+    /// Remove and return the earliest thread for which `pred` holds
+    pub fn pop_first(&mut self, pred: impl Fn(ThreadId) -> bool) -> Option<ThreadId> {
+        let pos = self.entries.iter().position(|&(_, tid)| pred(tid))?;
+        Some(self.entries.remove(pos).1)
+    }
+
+    /// Remove and return the earliest thread if its key is at or before `now`
+    pub fn pop_due(&mut self, now: u64) -> Option<ThreadId> {
+        match self.entries.first() {
+            Some(&(key, _)) if key <= now => self.pop(),
+            _ => None,
+        }
+    }
+
+    /// Earliest key
+    pub fn first_key(&self) -> Option<u64> {
+        self.entries.first().map(|&(key, _)| key)
+    }
+
+    /// Remove `tid` wherever it is
+    pub fn remove(&mut self, tid: ThreadId) -> bool {
+        match self.entries.iter().position(|&(_, t)| t == tid) {
+            Some(pos) => {
+                self.entries.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of queued threads
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no threads are queued
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn params(capacity: u64, relative_deadline: u64, period: u64) -> DeadlineParams {
+        DeadlineParams { capacity, relative_deadline, period }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(params(2 * MS, 5 * MS, 10 * MS).validate().is_ok());
+        assert!(params(2 * MS, 10 * MS, 10 * MS).validate().is_ok());
+        assert_eq!(params(6 * MS, 5 * MS, 10 * MS).validate(), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(params(2 * MS, 11 * MS, 10 * MS).validate(), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(params(1_000, 5 * MS, 10 * MS).validate(), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(params(0, 0, 0).validate(), Err(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_utilization() {
+        assert_eq!(params(2 * MS, 5 * MS, 10 * MS).utilization_ppm(), 200_000);
+        // Rounded up, never under-reserved
+        assert_eq!(params(1, 3, 3).utilization_ppm(), 333_334);
+    }
+
+    #[test]
+    fn test_admission() {
+        let half = params(5 * MS, 10 * MS, 10 * MS);
+        let before = admitted_ppm();
+        assert!(admit(&half).is_ok());
+        assert_eq!(admit(&half), Err(RX_ERR_NO_RESOURCES));
+        release(&half);
+        assert_eq!(admitted_ppm(), before);
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut state = DeadlineState::new(params(2 * MS, 5 * MS, 10 * MS));
+        state.replenish(100);
+        assert_eq!(state.deadline, 100 + 5 * MS);
+        assert_eq!(state.remaining, 2 * MS);
+
+        // Waking again inside the period keeps the budget
+        state.charge(MS);
+        state.replenish(100 + 3 * MS);
+        assert_eq!(state.remaining, MS);
+
+        state.charge(2 * MS);
+        assert!(state.is_throttled());
+
+        state.replenish(100 + 10 * MS);
+        assert_eq!(state.remaining, 2 * MS);
+        assert_eq!(state.deadline, 100 + 15 * MS);
+    }
+
+    #[test]
+    fn test_edf_order() {
+        let mut queue = TimeQueue::new();
+        queue.insert(30, 1);
+        queue.insert(10, 2);
+        queue.insert(20, 3);
+        queue.insert(10, 4);
+
+        assert_eq!(queue.pop_due(5), None);
+        assert_eq!(queue.pop_first(|tid| tid == 3), Some(3));
+        queue.insert(20, 3);
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(4));
+        assert!(queue.remove(1));
+        assert_eq!(queue.first_key(), Some(20));
+        assert_eq!(queue.pop_due(25), Some(3));
+        assert!(queue.is_empty());
+    }
+}
@@ -15,6 +15,18 @@
 //! - **Round-robin**: Threads at same priority scheduled in FIFO order
 //! - **Preemptive**: Timer tick triggers context switch
 //! - **Per-CPU**: Each CPU has its own run queue
+//! - **Deadline class**: Threads with a deadline profile run ahead of all
+//!   fixed-priority threads, earliest deadline first (see [`deadline`])
+//! - **Affinity**: A CPU only picks threads whose affinity mask includes
+//!   it, passing over the rest in queue order
+//! - **IPC lending**: A thread blocked in a channel call lends its
+//!   priority to the thread that takes its message until the reply, and
+//!   hands its time slice straight to that channel's reader
+//!   ([`lend_priority`], [`yield_to`])
+//! - **NUMA**: Among the runnable threads of the highest priority, a CPU
+//!   takes the first whose home node is its own (or who has none) before
+//!   the others, keeping threads near their memory without ever idling
+//!   for it
 //!
 //! # Thread States
 //!
@@ -39,8 +51,16 @@
 //! ```
 
 
-use crate::kernel::thread::{Thread, ThreadId, ThreadState, BlockReason, PRIORITY_DEFAULT};
+use crate::kernel::lib::itrace;
+use crate::kernel::lib::ktrace;
+use crate::kernel::lib::perfmon;
+use crate::kernel::mp;
+use crate::kernel::numa::{self, NUMA_NODE_ANY};
+use crate::kernel::percpu;
+use crate::kernel::sync::SpinLockIrqSave;
+use crate::kernel::thread::{self, get_thread_by_id, CpuMask, Thread, ThreadId, ThreadPriority, ThreadRef, ThreadState, BlockReason, PRIORITY_DEFAULT, TID_INVALID, CPU_MASK_ALL};
 use crate::rustux::types::*;
+use crate::rustux::types::err::RX_ERR_INVALID_ARGS;
 use crate::kernel::vm::Result;
 use alloc::collections::VecDeque;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
@@ -48,6 +68,16 @@ use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 // Import logging macros
 use crate::{log_debug, log_info, log_trace};
 
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(SCHED_PRIORITY_LENDS, "kernel.sched.priority_lends");
+KCOUNTER!(SCHED_DIRECTED_YIELDS, "kernel.sched.directed_yields");
+
+pub mod deadline;
+
+use deadline::{DeadlineParams, DeadlineState, TimeQueue};
+
 /// ============================================================================
 /// Scheduler Configuration
 /// ============================================================================
@@ -75,6 +105,12 @@ pub struct RunQueue {
     /// Run queues for each priority level
     queues: [VecDeque<ThreadId>; N_PRIORITIES],
 
+    /// Runnable deadline threads, by absolute deadline
+    deadline: TimeQueue,
+
+    /// Deadline threads out of budget, by end of period
+    throttled: TimeQueue,
+
     /// Currently running thread on this CPU
     current: Option<ThreadId>,
 
@@ -108,6 +144,8 @@ impl RunQueue {
                 VecDeque::new(), VecDeque::new(), VecDeque::new(), VecDeque::new(),
                 VecDeque::new(), VecDeque::new(), VecDeque::new(), VecDeque::new(),
             ],
+            deadline: TimeQueue::new(),
+            throttled: TimeQueue::new(),
             current: None,
             preempt_pending: AtomicBool::new(false),
             current_time_slice: DEFAULT_TIME_SLICE,
@@ -130,8 +168,35 @@ impl RunQueue {
         );
     }
 
+    /// Add a deadline thread to the run queue
+    pub fn enqueue_deadline(&mut self, tid: ThreadId, deadline: u64) {
+        self.deadline.insert(deadline, tid);
+        self.stats.ready_count += 1;
+
+        log_trace!("Enqueued deadline thread: tid={} deadline={}", tid, deadline);
+    }
+
+    /// Park a deadline thread that is out of budget until `release`
+    pub fn throttle(&mut self, tid: ThreadId, release: u64) {
+        self.throttled.insert(release, tid);
+
+        log_trace!("Throttled deadline thread: tid={} until={}", tid, release);
+    }
+
+    /// Take a throttled thread whose period has ended by `now`
+    pub fn release_due(&mut self, now: u64) -> Option<ThreadId> {
+        self.throttled.pop_due(now)
+    }
+
     /// Remove a thread from the run queue
     pub fn dequeue(&mut self, tid: ThreadId) -> bool {
+        if self.throttled.remove(tid) {
+            return true;
+        }
+        if self.deadline.remove(tid) {
+            self.stats.ready_count -= 1;
+            return true;
+        }
         for queue in &mut self.queues {
             if let Some(pos) = queue.iter().position(|&t| t == tid) {
                 queue.remove(pos);
@@ -142,12 +207,56 @@ impl RunQueue {
         false
     }
 
+    /// Move a queued fixed-priority thread to the head of its queue
+    ///
+    /// Returns false if `tid` isn't in a fixed-priority queue.
+    pub fn move_to_front(&mut self, tid: ThreadId) -> bool {
+        for queue in &mut self.queues {
+            if let Some(pos) = queue.iter().position(|&t| t == tid) {
+                queue.remove(pos);
+                queue.push_front(tid);
+                return true;
+            }
+        }
+        false
+    }
+
     /// Select the next thread to run
-    pub fn select(&mut self) -> Option<ThreadId> {
-        // Find highest priority non-empty queue
-        for i in 0..N_PRIORITIES {
-            if !self.queues[i].is_empty() {
-                let tid = self.queues[i].pop_front().unwrap();
+    ///
+    /// Threads for which `can_run` is false are passed over and keep their
+    /// place in the queue.
+    pub fn select(&mut self, can_run: impl Fn(ThreadId) -> bool) -> Option<ThreadId> {
+        self.select_preferring(can_run, |_| true)
+    }
+
+    /// Select the next thread to run, favouring `prefer`red threads
+    ///
+    /// Like [`select`](Self::select), except that within the highest
+    /// priority with a runnable thread, the first runnable thread for
+    /// which `prefer` holds is taken ahead of those queued before it.
+    /// Deadline order is never changed.
+    pub fn select_preferring(
+        &mut self,
+        can_run: impl Fn(ThreadId) -> bool,
+        prefer: impl Fn(ThreadId) -> bool,
+    ) -> Option<ThreadId> {
+        // Deadline threads first, earliest deadline first
+        if let Some(tid) = self.deadline.pop_first(&can_run) {
+            self.stats.ready_count -= 1;
+            self.stats.schedules += 1;
+            return Some(tid);
+        }
+
+        // Find highest priority queue with a runnable thread
+        for i in (0..N_PRIORITIES).rev() {
+            let queue = &self.queues[i];
+            if let Some(first) = queue.iter().position(|&tid| can_run(tid)) {
+                let pos = queue
+                    .iter()
+                    .skip(first)
+                    .position(|&tid| can_run(tid) && prefer(tid))
+                    .map_or(first, |pos| first + pos);
+                let tid = self.queues[i].remove(pos).unwrap();
                 self.stats.ready_count -= 1;
                 self.stats.schedules += 1;
                 return Some(tid);
@@ -204,12 +313,12 @@ impl RunQueue {
 
     /// Check if run queue is empty
     pub fn is_empty(&self) -> bool {
-        self.queues.iter().all(|q| q.is_empty())
+        self.deadline.is_empty() && self.queues.iter().all(|q| q.is_empty())
     }
 
     /// Get number of threads in run queue
     pub fn len(&self) -> usize {
-        self.queues.iter().map(|q| q.len()).sum()
+        self.deadline.len() + self.queues.iter().map(|q| q.len()).sum::<usize>()
     }
 
     /// Get statistics
@@ -278,9 +387,9 @@ static mut GLOBAL_SCHEDULER: Option<Scheduler> = None;
 
 /// Global scheduler lock
 ///
-/// This protects the global scheduler state.
-/// In a real implementation, this would be a spinlock or per-CPU data.
-static SCHEDULER_LOCK: AtomicBool = AtomicBool::new(false);
+/// This protects the global scheduler state. The timer tick takes it in
+/// interrupt context, so it keeps interrupts off while held.
+static SCHEDULER_LOCK: SpinLockIrqSave<()> = SpinLockIrqSave::new(());
 
 /// Scheduler structure
 pub struct Scheduler {
@@ -306,6 +415,19 @@ impl Scheduler {
 
     /// Schedule the next thread to run
     pub fn schedule(&mut self) -> Option<ThreadId> {
+        let prev = self.runqueue.current();
+        let now = Self::current_time();
+
+        // Charge a deadline thread for the time it just ran
+        if let Some(thread) = prev.and_then(Self::get_thread_ref) {
+            if let Some(state) = thread.deadline.lock().as_mut() {
+                state.charge(now.saturating_sub(self.runqueue.last_schedule_time()));
+            }
+        }
+
+        // Deadline threads whose period ended are runnable again
+        self.release_throttled(now);
+
         // Check if we need to preempt
         if self.runqueue.is_preempt_pending() {
             let current = self.runqueue.current();
@@ -317,18 +439,19 @@ impl Scheduler {
                         // Move back to ready state
                         thread.set_state(ThreadState::Ready);
 
-                        // Get thread priority
-                        let priority = thread.priority();
-
                         // Re-enqueue
-                        self.runqueue.enqueue(tid, priority);
+                        self.enqueue_thread(tid, &thread, now);
                     }
                 }
             }
         }
 
-        // Select next thread
-        let next = self.runqueue.select();
+        // Select next thread this CPU may run
+        let cpu = percpu::current_cpu_num();
+        let node = numa::cpu_node(cpu);
+        let next = self
+            .runqueue
+            .select_preferring(|tid| Self::can_run_on(tid, cpu), |tid| Self::is_home(tid, node));
 
         // If no thread to run, use idle thread
         let tid = if let Some(tid) = next {
@@ -343,9 +466,17 @@ impl Scheduler {
 
         // Update current thread
         self.runqueue.set_current(Some(tid));
+        if let Some(thread) = Self::get_thread_ref(tid) {
+            thread.set_state(ThreadState::Running);
+        }
+        if prev != Some(tid) {
+            ktrace::write(ktrace::TAG_CONTEXT_SWITCH, 0, prev.unwrap_or(TID_INVALID), tid);
+            perfmon::context_switch(prev, tid);
+            itrace::context_switch(prev, tid);
+            Self::account_switch(prev, tid, now);
+        }
 
         // Update schedule time
-        let now = Self::current_time();
         self.runqueue.update_schedule_time(now);
 
         log_trace!("Scheduled thread: tid={} cpu={}", tid, self.cpu_id);
@@ -356,16 +487,55 @@ impl Scheduler {
     /// Make a thread ready to run
     pub fn ready(&mut self, tid: ThreadId) {
         if let Some(thread) = Self::get_thread_ref(tid) {
-            // Get thread priority
-            let priority = thread.priority();
-
             // Set state to ready
             thread.set_state(ThreadState::Ready);
 
             // Add to run queue
-            self.runqueue.enqueue(tid, priority);
+            self.enqueue_thread(tid, &thread, Self::current_time());
+
+            // A deadline thread may have an earlier deadline than the
+            // current one, and always beats a fixed-priority thread
+            if thread.deadline.lock().is_some() {
+                self.runqueue.request_preempt();
+            }
+
+            log_debug!("Thread ready: tid={} priority={}", tid, thread.priority());
+        }
+    }
+
+    /// Add a ready thread to the queue for its class
+    ///
+    /// A deadline thread starts a new period if its last one has ended,
+    /// and is throttled until the period ends if it has no budget left.
+    fn enqueue_thread(&mut self, tid: ThreadId, thread: &Thread, now: u64) {
+        match thread.deadline.lock().as_mut() {
+            Some(state) => {
+                state.replenish(now);
+                if state.is_throttled() {
+                    self.runqueue.throttle(tid, state.period_end);
+                } else {
+                    self.runqueue.enqueue_deadline(tid, state.deadline);
+                }
+            }
+            None => self.runqueue.enqueue(tid, thread.effective_priority()),
+        }
+    }
 
-            log_debug!("Thread ready: tid={} priority={}", tid, priority);
+    /// Requeue throttled deadline threads whose period has ended
+    fn release_throttled(&mut self, now: u64) {
+        while let Some(tid) = self.runqueue.release_due(now) {
+            if let Some(thread) = Self::get_thread_ref(tid) {
+                self.enqueue_thread(tid, &thread, now);
+            }
+        }
+    }
+
+    /// Move a queued thread to the queue for its current class
+    fn requeue(&mut self, tid: ThreadId) {
+        if self.runqueue.dequeue(tid) {
+            if let Some(thread) = Self::get_thread_ref(tid) {
+                self.enqueue_thread(tid, &thread, Self::current_time());
+            }
         }
     }
 
@@ -376,9 +546,8 @@ impl Scheduler {
                 // Move to ready state
                 thread.set_state(ThreadState::Ready);
 
-                // Re-enqueue with same priority
-                let priority = thread.priority();
-                self.runqueue.enqueue(tid, priority);
+                // Re-enqueue in its class
+                self.enqueue_thread(tid, &thread, Self::current_time());
 
                 // Update statistics
                 self.runqueue.stats.yields += 1;
@@ -388,6 +557,26 @@ impl Scheduler {
         }
     }
 
+    /// Give the rest of the current thread's time slice to `tid`
+    ///
+    /// `tid` goes to the head of its queue and the current thread to the
+    /// back of its own. Returns false, leaving both alone, if `tid` isn't
+    /// queued or can't run on this CPU.
+    pub fn yield_to(&mut self, tid: ThreadId) -> bool {
+        let cpu = percpu::current_cpu_num();
+        if Some(tid) == self.runqueue.current() || !Self::can_run_on(tid, cpu) {
+            return false;
+        }
+        if !self.runqueue.move_to_front(tid) {
+            return false;
+        }
+
+        self.yield_current();
+        self.runqueue.request_preempt();
+        log_trace!("Directed yield: to={}", tid);
+        true
+    }
+
     /// Block the current thread
     pub fn block_current(&mut self, reason: BlockReason) {
         if let Some(tid) = self.runqueue.current() {
@@ -415,10 +604,31 @@ impl Scheduler {
         }
     }
 
+    /// Undo a switch from `prev` to `next` that the CPU couldn't make
+    ///
+    /// `prev` stays current and `next` goes back to its queue.
+    pub fn cancel_switch(&mut self, prev: ThreadId, next: ThreadId) {
+        if let Some(thread) = Self::get_thread_ref(next) {
+            thread.set_state(ThreadState::Ready);
+            self.enqueue_thread(next, &thread, Self::current_time());
+        }
+        // A preempted thread was queued again
+        self.runqueue.dequeue(prev);
+        if let Some(thread) = Self::get_thread_ref(prev) {
+            thread.set_state(ThreadState::Running);
+        }
+        self.runqueue.set_current(Some(prev));
+    }
+
     /// Exit the current thread
     pub fn exit_current(&mut self, code: rx_status_t) {
         if let Some(tid) = self.runqueue.current() {
             if let Some(thread) = Self::get_thread_ref(tid) {
+                // Give back its deadline reservation
+                if let Some(state) = thread.deadline.lock().take() {
+                    deadline::release(&state.params);
+                }
+
                 // Exit the thread
                 thread.exit(code);
 
@@ -434,6 +644,7 @@ impl Scheduler {
 
         // Check if time slice expired
         let now = Self::current_time();
+        self.release_throttled(now);
         let elapsed = now.saturating_sub(self.runqueue.last_schedule_time());
 
         if elapsed >= self.runqueue.time_slice() {
@@ -453,31 +664,49 @@ impl Scheduler {
 
     /// Get current time (monotonic)
     fn current_time() -> u64 {
-        // This would call into the timer subsystem
-        // For now, return a stub value
-        #[cfg(target_arch = "aarch64")]
-        {
-            crate::arch::arm64::timer::arm64_current_time()
-        }
+        crate::kernel::timer::current_time()
+    }
 
-        #[cfg(target_arch = "x86_64")]
-        {
-            crate::kernel::arch::amd64::timer::amd64_current_time()
+    /// Charge the outgoing thread its CPU time and the incoming one its
+    /// queue time
+    fn account_switch(prev: Option<ThreadId>, next: ThreadId, now: u64) {
+        if let Some(thread) = prev.and_then(Self::get_thread_ref) {
+            thread.runtime.switch_out(now);
         }
-
-        #[cfg(target_arch = "riscv64")]
-        {
-            crate::arch::riscv64::timer::riscv_current_time()
+        if let Some(thread) = Self::get_thread_ref(next) {
+            thread.runtime.switch_in(now);
         }
     }
 
-    /// Get a reference to a thread
+    /// Look up a thread in the global thread registry
+    fn get_thread_ref(tid: ThreadId) -> Option<ThreadRef> {
+        get_thread_by_id(tid)
+    }
+
+    /// Whether `cpu` may run thread `tid`
     ///
-    /// This is a stub - in a real implementation, this would
-    /// look up the thread in a global thread table.
-    fn get_thread_ref(_tid: ThreadId) -> Option<&'static Thread> {
-        // Stub implementation
-        None
+    /// Threads the registry doesn't know have no affinity to check.
+    fn can_run_on(tid: ThreadId, cpu: u32) -> bool {
+        Self::get_thread_ref(tid).map_or(true, |thread| thread.can_run_on(cpu))
+    }
+
+    /// Whether NUMA `node` is the home of `tid`, or it has none
+    fn is_home(tid: ThreadId, node: u32) -> bool {
+        Self::get_thread_ref(tid).map_or(true, |thread| {
+            let home = thread.home_node();
+            home == NUMA_NODE_ANY || home == node
+        })
+    }
+
+    /// Preempt the current thread if it may no longer run on this CPU,
+    /// because its affinity changed or it was frozen
+    fn check_current(&mut self) {
+        let cpu = percpu::current_cpu_num();
+        if let Some(tid) = self.runqueue.current() {
+            if !Self::can_run_on(tid, cpu) {
+                self.runqueue.request_preempt();
+            }
+        }
     }
 }
 
@@ -487,8 +716,11 @@ impl Scheduler {
 
 /// Initialize the global scheduler
 pub fn init_scheduler(cpu_id: u64) {
-    unsafe {
-        GLOBAL_SCHEDULER = Some(Scheduler::new(cpu_id));
+    {
+        let _guard = SCHEDULER_LOCK.lock();
+        unsafe {
+            GLOBAL_SCHEDULER = Some(Scheduler::new(cpu_id));
+        }
     }
     log_info!("Scheduler initialized for CPU {}", cpu_id);
 }
@@ -498,6 +730,16 @@ pub fn schedule() -> Option<ThreadId> {
     with_scheduler_mut(|sched| sched.schedule())
 }
 
+/// The thread this CPU's scheduler runs
+pub fn current() -> Option<ThreadId> {
+    with_scheduler_mut(|sched| sched.runqueue.current())
+}
+
+/// Undo a switch from `prev` to `next` that the CPU couldn't make
+pub fn cancel_switch(prev: ThreadId, next: ThreadId) {
+    with_scheduler_mut(|sched| sched.cancel_switch(prev, next));
+}
+
 /// Make a thread ready to run
 pub fn ready(tid: ThreadId) {
     with_scheduler_mut(|sched| sched.ready(tid))
@@ -508,6 +750,18 @@ pub fn yield_current() {
     with_scheduler_mut(|sched| sched.yield_current());
 }
 
+/// Give the rest of the current thread's time slice to `tid`, if it is
+/// ready to run here
+///
+/// Returns whether it was.
+pub fn yield_to(tid: ThreadId) -> bool {
+    let yielded = with_scheduler_mut(|sched| sched.yield_to(tid));
+    if yielded {
+        SCHED_DIRECTED_YIELDS.add(1);
+    }
+    yielded
+}
+
 /// Block the current thread
 pub fn block_current(reason: BlockReason) {
     with_scheduler_mut(|sched| sched.block_current(reason));
@@ -528,6 +782,172 @@ pub fn timer_tick() {
     with_scheduler_mut(|sched| sched.timer_tick());
 }
 
+/// Whether the scheduler wants the current thread preempted
+///
+/// Reads the flag without the scheduler lock, so it is safe on interrupt
+/// exit even if the interrupted code holds the lock.
+pub fn preempt_pending() -> bool {
+    unsafe {
+        GLOBAL_SCHEDULER.as_ref().map_or(false, |sched| sched.runqueue.is_preempt_pending())
+    }
+}
+
+/// Preempt the current thread: requeue it and pick the next one
+///
+/// Called on the outermost interrupt exit when a preemption is pending.
+pub fn preempt() -> Option<ThreadId> {
+    with_scheduler_mut(|sched| {
+        // The request may have come from interrupt context rather than the
+        // scheduler, so make sure schedule() requeues the current thread
+        sched.runqueue.preempt_pending.store(true, Ordering::Relaxed);
+        let next = sched.schedule();
+        sched.runqueue.clear_preempt();
+        next
+    })
+}
+
+/// Put a thread in the deadline class with `params`, or back in its fixed
+/// priority class with `None`
+///
+/// Fails with `RX_ERR_INVALID_ARGS` for bad parameters and
+/// `RX_ERR_NO_RESOURCES` if admission control refuses them; either way the
+/// thread keeps its current class.
+pub fn set_deadline(thread: &Thread, params: Option<DeadlineParams>) -> core::result::Result<(), rx_status_t> {
+    if let Some(params) = &params {
+        params.validate()?;
+    }
+
+    {
+        let mut state = thread.deadline.lock();
+
+        // Release the old reservation first so a thread can swap one
+        // profile for another that only fits without it
+        if let Some(old) = state.as_ref() {
+            deadline::release(&old.params);
+        }
+        if let Some(params) = &params {
+            if let Err(err) = deadline::admit(params) {
+                if let Some(old) = state.as_ref() {
+                    let _ = deadline::admit(&old.params);
+                }
+                return Err(err);
+            }
+        }
+        *state = params.map(DeadlineState::new);
+    }
+
+    with_scheduler_mut(|sched| sched.requeue(thread.tid()));
+    Ok(())
+}
+
+/// Lend `priority` to `thread` until it answers transaction `txid`
+///
+/// A loan only ever raises the thread's effective priority; it replaces
+/// any earlier loan. A queued thread moves to the queue for its new
+/// priority.
+pub fn lend_priority(thread: &Thread, priority: ThreadPriority, txid: u32) {
+    if priority <= thread.priority() {
+        return_lent_priority(thread);
+        return;
+    }
+
+    thread.set_lent_priority(priority, txid);
+    SCHED_PRIORITY_LENDS.add(1);
+    with_scheduler_mut(|sched| sched.requeue(thread.tid()));
+}
+
+/// Give back a priority lent with [`lend_priority`]
+pub fn return_lent_priority(thread: &Thread) {
+    if thread.lent_priority().is_some() {
+        thread.clear_lent_priority();
+        with_scheduler_mut(|sched| sched.requeue(thread.tid()));
+    }
+}
+
+/// Restrict a thread to the CPUs in `mask`
+///
+/// Fails with `RX_ERR_INVALID_ARGS` if no CPU in `mask` is online. A thread
+/// running on a CPU it leaves is preempted and requeued.
+pub fn set_affinity(thread: &Thread, mask: CpuMask) -> core::result::Result<(), rx_status_t> {
+    if mask & mp::mp_get_online_mask() == 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    thread.set_cpu_affinity(mask);
+    with_scheduler_mut(|sched| sched.check_current());
+    Ok(())
+}
+
+/// Freeze every user thread for system suspend
+///
+/// Frozen threads keep their place in the run queue and can still be
+/// woken, but are never selected; one that is running is preempted.
+/// Returns the number of threads frozen.
+pub fn freeze_user_threads() -> usize {
+    let mut frozen = 0;
+
+    thread::for_each_thread(|thread| {
+        if thread.pid().is_some() && !thread.is_frozen() {
+            thread.set_frozen(true);
+            frozen += 1;
+        }
+    });
+
+    with_scheduler_mut(|sched| sched.check_current());
+    frozen
+}
+
+/// Let every thread frozen by [`freeze_user_threads`] run again
+///
+/// Returns the number of threads thawed.
+pub fn thaw_threads() -> usize {
+    let mut thawed = 0;
+
+    thread::for_each_thread(|thread| {
+        if thread.is_frozen() {
+            thread.set_frozen(false);
+            thawed += 1;
+        }
+    });
+
+    if thawed > 0 {
+        with_scheduler_mut(|sched| sched.runqueue.request_preempt());
+    }
+    thawed
+}
+
+/// Move every thread off `cpu` before it goes offline
+///
+/// A thread whose affinity leaves it no other online CPU can run anywhere
+/// again, as on a system that never had its mask's CPUs. Returns the
+/// number of threads whose affinity was reset.
+pub fn migrate_from_cpu(cpu: u32) -> usize {
+    let online = mp::mp_get_online_mask() & !mp::cpu_num_to_mask(cpu);
+    let mut reset = 0;
+
+    thread::for_each_thread(|thread| {
+        if thread.cpu_affinity() & online == 0 {
+            log_info!(
+                "sched: thread {} had no CPU but {}, now runs anywhere",
+                thread.tid(),
+                cpu
+            );
+            thread.set_cpu_affinity(CPU_MASK_ALL);
+            reset += 1;
+        }
+    });
+
+    // The run queue is shared, so the only thread to move is whichever is
+    // on the CPU going away
+    with_scheduler_mut(|sched| {
+        if sched.cpu_id == cpu as u64 {
+            sched.runqueue.request_preempt();
+        }
+    });
+
+    reset
+}
+
 /// Get scheduler statistics
 pub fn get_stats() -> Option<SchedulerStats> {
     unsafe {
@@ -540,22 +960,13 @@ fn with_scheduler<F, R>(f: F) -> R
 where
     F: FnOnce(&Scheduler) -> R,
 {
-    // Acquire lock
-    while SCHEDULER_LOCK.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-        core::hint::spin_loop();
-    }
-
-    let result = unsafe {
+    let _guard = SCHEDULER_LOCK.lock();
+    unsafe {
         GLOBAL_SCHEDULER.as_ref().map_or_else(
             || panic!("Scheduler not initialized"),
             |sched| f(sched),
         )
-    };
-
-    // Release lock
-    SCHEDULER_LOCK.store(false, Ordering::Release);
-
-    result
+    }
 }
 
 /// Execute a function with mutable scheduler
@@ -563,22 +974,13 @@ fn with_scheduler_mut<F, R>(f: F) -> R
 where
     F: FnOnce(&mut Scheduler) -> R,
 {
-    // Acquire lock
-    while SCHEDULER_LOCK.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-        core::hint::spin_loop();
-    }
-
-    let result = unsafe {
+    let _guard = SCHEDULER_LOCK.lock();
+    unsafe {
         GLOBAL_SCHEDULER.as_mut().map_or_else(
             || panic!("Scheduler not initialized"),
             |sched| f(sched),
         )
-    };
-
-    // Release lock
-    SCHEDULER_LOCK.store(false, Ordering::Release);
-
-    result
+    }
 }
 
 /// ============================================================================
@@ -622,6 +1024,87 @@ mod tests {
         assert!(rq.time_slice() <= MAX_TIME_SLICE);
     }
 
+    #[test]
+    fn test_select_skips_unrunnable() {
+        let mut rq = RunQueue::new();
+        rq.enqueue(1, 200);
+        rq.enqueue(2, 200);
+        rq.enqueue(3, 10);
+        rq.enqueue_deadline(4, 1_000);
+
+        // Passed-over threads keep their place
+        assert_eq!(rq.select(|tid| tid != 1 && tid != 4), Some(2));
+        assert_eq!(rq.select(|tid| tid == 3), Some(3));
+        assert_eq!(rq.select(|_| true), Some(4));
+        assert_eq!(rq.select(|_| true), Some(1));
+        assert!(rq.is_empty());
+    }
+
+    #[test]
+    fn test_select_highest_priority_first() {
+        let mut rq = RunQueue::new();
+        rq.enqueue(1, PRIORITY_DEFAULT);
+        rq.enqueue(2, 10);
+        rq.enqueue(3, 250);
+
+        assert_eq!(rq.select(|_| true), Some(3));
+        assert_eq!(rq.select(|_| true), Some(1));
+        assert_eq!(rq.select(|_| true), Some(2));
+    }
+
+    #[test]
+    fn test_cancel_switch() {
+        let prev = Thread::new(0, 0, 0, PRIORITY_DEFAULT).unwrap().into_ref();
+        let next = Thread::new(0, 0, 0, PRIORITY_DEFAULT).unwrap().into_ref();
+        thread::register_thread(prev.clone());
+        thread::register_thread(next.clone());
+
+        // As preempt() leaves it: prev requeued, next current
+        let mut sched = Scheduler::new(0);
+        sched.runqueue.enqueue(prev.tid(), PRIORITY_DEFAULT);
+        sched.runqueue.set_current(Some(next.tid()));
+
+        sched.cancel_switch(prev.tid(), next.tid());
+        assert_eq!(sched.runqueue.current(), Some(prev.tid()));
+        assert_eq!(prev.state(), ThreadState::Running);
+        assert_eq!(next.state(), ThreadState::Ready);
+        assert_eq!(sched.runqueue.select(|_| true), Some(next.tid()));
+        assert!(sched.runqueue.is_empty());
+
+        thread::unregister_thread(prev.tid());
+        thread::unregister_thread(next.tid());
+    }
+
+    #[test]
+    fn test_select_preferring() {
+        let mut rq = RunQueue::new();
+        rq.enqueue(1, PRIORITY_DEFAULT);
+        rq.enqueue(2, PRIORITY_DEFAULT);
+        rq.enqueue(3, PRIORITY_DEFAULT);
+        rq.enqueue(4, 10);
+
+        // Preferred thread jumps its level, unrunnable ones stay skipped
+        assert_eq!(rq.select_preferring(|tid| tid != 3, |tid| tid == 3 || tid == 2), Some(2));
+        // No preferred thread: queue order, never a lower priority
+        assert_eq!(rq.select_preferring(|_| true, |tid| tid == 4), Some(1));
+        assert_eq!(rq.select_preferring(|_| true, |_| false), Some(3));
+        assert_eq!(rq.select_preferring(|_| true, |_| false), Some(4));
+    }
+
+    #[test]
+    fn test_move_to_front() {
+        let mut rq = RunQueue::new();
+        rq.enqueue(1, PRIORITY_DEFAULT);
+        rq.enqueue(2, PRIORITY_DEFAULT);
+        rq.enqueue_deadline(3, 1_000);
+
+        assert!(rq.move_to_front(2));
+        assert!(!rq.move_to_front(3));
+        assert!(!rq.move_to_front(4));
+        assert_eq!(rq.select(|tid| tid != 3), Some(2));
+        assert_eq!(rq.select(|tid| tid != 3), Some(1));
+    }
+
     #[test]
     fn test_stats_new() {
         let stats = SchedulerStats::new();
@@ -32,6 +32,7 @@
 //! ```
 
 
+use crate::kernel::preempt;
 use crate::kernel::thread::ThreadId;
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
@@ -157,6 +158,7 @@ impl Event {
     ///
     /// If already signaled, returns immediately.
     /// Otherwise, blocks until signaled.
+    #[track_caller]
     pub fn wait(&self) {
         self.wait_deadline(u64::MAX);
     }
@@ -171,8 +173,10 @@ impl Event {
     ///
     /// - `Ok(())` if signaled
     /// - `Err(RX_ERR_TIMED_OUT)` if deadline reached
+    #[track_caller]
     pub fn wait_deadline(&self, deadline: u64) -> Result {
         self.validate();
+        preempt::might_block();
 
         // Fast path: already signaled
         if self.is_signaled() {
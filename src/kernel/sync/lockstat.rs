@@ -0,0 +1,398 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Lock Statistics
+//!
+//! Contention and hold-time statistics for [`SpinMutex`](super::SpinMutex)
+//! and [`Mutex`](super::Mutex), and contention for [`RwLock`](super::RwLock).
+//!
+//! # Design
+//!
+//! - **Counters**: Every contended acquisition bumps
+//!   `kernel.lock.spin_contended`, `kernel.lock.mutex_contended` or
+//!   `kernel.lock.rwlock_contended`, in all builds.
+//! - **Per-lock table** (debug builds): A fixed, lock-free table keyed by
+//!   lock address. A lock gets an entry the first time it is contended or
+//!   held for [`LONG_HOLD_NS`] or more; from then on the entry tracks its
+//!   contentions, spins, longest wait and longest hold, and where it was
+//!   taken. Locks that never get there cost a timestamp per acquisition
+//!   and a table probe per release.
+//! - **Stale entries**: Entries outlive their locks. A new lock at a freed
+//!   lock's address shares its entry until the next `lockstat reset`.
+//!
+//! Locks that find the table full are counted in
+//! `kernel.lock.stat_dropped`.
+
+use core::panic::Location;
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+#[cfg(debug_assertions)]
+use crate::kernel::timer;
+#[cfg(debug_assertions)]
+use alloc::vec::Vec;
+#[cfg(debug_assertions)]
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+KCOUNTER!(LOCK_SPIN_CONTENDED, "kernel.lock.spin_contended");
+KCOUNTER!(LOCK_MUTEX_CONTENDED, "kernel.lock.mutex_contended");
+KCOUNTER!(LOCK_RWLOCK_CONTENDED, "kernel.lock.rwlock_contended");
+KCOUNTER!(LOCK_STAT_DROPPED, "kernel.lock.stat_dropped");
+
+/// Holds at least this long put a lock in the table
+pub const LONG_HOLD_NS: u64 = 100_000;
+
+/// Kind of lock
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// [`SpinMutex`](super::SpinMutex)
+    Spin = 1,
+
+    /// [`Mutex`](super::Mutex)
+    Mutex = 2,
+
+    /// [`RwLock`](super::RwLock)
+    RwLock = 3,
+}
+
+impl LockKind {
+    #[cfg(debug_assertions)]
+    const fn from_raw(raw: u8) -> Self {
+        match raw {
+            2 => Self::Mutex,
+            3 => Self::RwLock,
+            _ => Self::Spin,
+        }
+    }
+}
+
+/// Record a contended acquisition of the lock at `lock`
+///
+/// `spins` is how many times the acquirer retried and `wait` how long it
+/// waited in nanoseconds; `site` is where it took the lock.
+pub fn contended(lock: usize, kind: LockKind, spins: u64, wait: u64, site: &'static Location<'static>) {
+    match kind {
+        LockKind::Spin => LOCK_SPIN_CONTENDED.add(1),
+        LockKind::Mutex => LOCK_MUTEX_CONTENDED.add(1),
+        LockKind::RwLock => LOCK_RWLOCK_CONTENDED.add(1),
+    }
+
+    #[cfg(debug_assertions)]
+    if let Some(stat) = find_or_insert(lock, kind, site) {
+        stat.contentions.fetch_add(1, Ordering::Relaxed);
+        stat.spins.fetch_add(spins, Ordering::Relaxed);
+        stat.max_wait.fetch_max(wait, Ordering::Relaxed);
+    }
+
+    #[cfg(not(debug_assertions))]
+    let _ = (lock, spins, wait, site);
+}
+
+/// Contended acquisitions since boot, across all locks
+pub fn contended_count(kind: LockKind) -> i64 {
+    match kind {
+        LockKind::Spin => LOCK_SPIN_CONTENDED.value(),
+        LockKind::Mutex => LOCK_MUTEX_CONTENDED.value(),
+        LockKind::RwLock => LOCK_RWLOCK_CONTENDED.value(),
+    }
+}
+
+/// ============================================================================
+/// Hold Tracking (debug builds)
+/// ============================================================================
+
+/// When a lock was taken and where, embedded in each lock in debug builds
+#[cfg(debug_assertions)]
+pub struct Holder {
+    since: AtomicU64,
+    site: AtomicPtr<Location<'static>>,
+}
+
+#[cfg(debug_assertions)]
+impl Holder {
+    /// A lock nobody has taken yet
+    pub const fn new() -> Self {
+        Self { since: AtomicU64::new(0), site: AtomicPtr::new(core::ptr::null_mut()) }
+    }
+
+    /// The lock was just taken at `site`
+    #[inline]
+    pub fn acquired(&self, site: &'static Location<'static>) {
+        self.since.store(timer::current_time(), Ordering::Relaxed);
+        self.site.store(site as *const _ as *mut _, Ordering::Relaxed);
+    }
+
+    /// The lock at `lock` is about to be released
+    ///
+    /// Call before releasing, while this is still the holder's.
+    #[inline]
+    pub fn released(&self, lock: usize, kind: LockKind) {
+        let hold = timer::current_time().saturating_sub(self.since.load(Ordering::Relaxed));
+        let site = self.site.load(Ordering::Relaxed) as *const Location<'static>;
+        if site.is_null() {
+            return;
+        }
+        // SAFETY: only ever set from a `&'static Location`
+        record_hold(lock, kind, hold, unsafe { &*site });
+    }
+}
+
+/// Record a `hold` nanoseconds long of the lock at `lock`, taken at `site`
+#[cfg(debug_assertions)]
+fn record_hold(lock: usize, kind: LockKind, hold: u64, site: &'static Location<'static>) {
+    let stat = if hold >= LONG_HOLD_NS { find_or_insert(lock, kind, site) } else { find(lock) };
+    if let Some(stat) = stat {
+        if stat.max_hold.fetch_max(hold, Ordering::Relaxed) < hold {
+            stat.max_hold_site.store(site as *const _ as *mut _, Ordering::Relaxed);
+        }
+    }
+}
+
+/// ============================================================================
+/// Per-Lock Table (debug builds)
+/// ============================================================================
+
+/// Table size, a power of two
+#[cfg(debug_assertions)]
+const TABLE_SIZE: usize = 512;
+
+/// Slots probed before giving up on a lock
+#[cfg(debug_assertions)]
+const MAX_PROBE: usize = 16;
+
+#[cfg(debug_assertions)]
+struct LockStat {
+    /// Lock address, 0 for a free slot
+    lock: AtomicUsize,
+    kind: AtomicU8,
+
+    /// Where the lock was taken when its entry was made
+    site: AtomicPtr<Location<'static>>,
+    contentions: AtomicU64,
+    spins: AtomicU64,
+    max_wait: AtomicU64,
+    max_hold: AtomicU64,
+
+    /// Where the lock was taken for the longest hold
+    max_hold_site: AtomicPtr<Location<'static>>,
+}
+
+#[cfg(debug_assertions)]
+impl LockStat {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Self = Self {
+        lock: AtomicUsize::new(0),
+        kind: AtomicU8::new(0),
+        site: AtomicPtr::new(core::ptr::null_mut()),
+        contentions: AtomicU64::new(0),
+        spins: AtomicU64::new(0),
+        max_wait: AtomicU64::new(0),
+        max_hold: AtomicU64::new(0),
+        max_hold_site: AtomicPtr::new(core::ptr::null_mut()),
+    };
+
+    fn clear(&self) {
+        self.contentions.store(0, Ordering::Relaxed);
+        self.spins.store(0, Ordering::Relaxed);
+        self.max_wait.store(0, Ordering::Relaxed);
+        self.max_hold.store(0, Ordering::Relaxed);
+        self.max_hold_site.store(core::ptr::null_mut(), Ordering::Relaxed);
+        self.lock.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(debug_assertions)]
+static TABLE: [LockStat; TABLE_SIZE] = [LockStat::EMPTY; TABLE_SIZE];
+
+#[cfg(debug_assertions)]
+fn slot_for(lock: usize, probe: usize) -> usize {
+    let hash = ((lock >> 3) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32;
+    (hash as usize + probe) & (TABLE_SIZE - 1)
+}
+
+/// The entry for `lock`, if it has one
+#[cfg(debug_assertions)]
+fn find(lock: usize) -> Option<&'static LockStat> {
+    for probe in 0..MAX_PROBE {
+        let stat = &TABLE[slot_for(lock, probe)];
+        match stat.lock.load(Ordering::Acquire) {
+            0 => return None,
+            found if found == lock => return Some(stat),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The entry for `lock`, made if need be
+#[cfg(debug_assertions)]
+fn find_or_insert(lock: usize, kind: LockKind, site: &'static Location<'static>) -> Option<&'static LockStat> {
+    for probe in 0..MAX_PROBE {
+        let stat = &TABLE[slot_for(lock, probe)];
+        match stat.lock.compare_exchange(0, lock, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                stat.kind.store(kind as u8, Ordering::Relaxed);
+                stat.site.store(site as *const _ as *mut _, Ordering::Relaxed);
+                return Some(stat);
+            }
+            Err(found) if found == lock => return Some(stat),
+            Err(_) => {}
+        }
+    }
+    LOCK_STAT_DROPPED.add(1);
+    None
+}
+
+/// One lock's statistics
+#[cfg(debug_assertions)]
+#[derive(Debug, Clone, Copy)]
+pub struct LockStatSnapshot {
+    pub lock: usize,
+    pub kind: LockKind,
+    pub site: Option<&'static Location<'static>>,
+    pub contentions: u64,
+    pub spins: u64,
+    pub max_wait: u64,
+    pub max_hold: u64,
+    pub max_hold_site: Option<&'static Location<'static>>,
+}
+
+#[cfg(debug_assertions)]
+fn location(ptr: *mut Location<'static>) -> Option<&'static Location<'static>> {
+    // SAFETY: only ever set from a `&'static Location`
+    unsafe { (ptr as *const Location<'static>).as_ref() }
+}
+
+/// Every tracked lock, most contended first
+#[cfg(debug_assertions)]
+pub fn snapshot() -> Vec<LockStatSnapshot> {
+    let mut stats: Vec<LockStatSnapshot> = TABLE
+        .iter()
+        .filter_map(|stat| {
+            let lock = stat.lock.load(Ordering::Acquire);
+            (lock != 0).then(|| LockStatSnapshot {
+                lock,
+                kind: LockKind::from_raw(stat.kind.load(Ordering::Relaxed)),
+                site: location(stat.site.load(Ordering::Relaxed)),
+                contentions: stat.contentions.load(Ordering::Relaxed),
+                spins: stat.spins.load(Ordering::Relaxed),
+                max_wait: stat.max_wait.load(Ordering::Relaxed),
+                max_hold: stat.max_hold.load(Ordering::Relaxed),
+                max_hold_site: location(stat.max_hold_site.load(Ordering::Relaxed)),
+            })
+        })
+        .collect();
+    stats.sort_by(|a, b| b.contentions.cmp(&a.contentions).then(b.max_hold.cmp(&a.max_hold)));
+    stats
+}
+
+/// Forget every tracked lock
+#[cfg(debug_assertions)]
+pub fn reset() {
+    for stat in TABLE.iter() {
+        stat.clear();
+    }
+}
+
+/// ============================================================================
+/// Shell Command
+/// ============================================================================
+
+/// Locks listed by `lockstat`
+#[cfg(debug_assertions)]
+const LOCKSTAT_SHOWN: usize = 20;
+
+fn cmd_lockstat(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    crate::println!(
+        "contended: {} spin, {} mutex, {} rwlock",
+        contended_count(LockKind::Spin),
+        contended_count(LockKind::Mutex),
+        contended_count(LockKind::RwLock)
+    );
+
+    #[cfg(debug_assertions)]
+    {
+        if argc >= 2 && argv[1] == "reset" {
+            reset();
+            return 0;
+        }
+
+        let stats = snapshot();
+        crate::println!("{:>18} {:>5} {:>10} {:>12} {:>12} {:>12}  site", "lock", "kind", "contended", "spins", "max wait", "max hold");
+        for stat in stats.iter().take(LOCKSTAT_SHOWN) {
+            let site = stat.max_hold_site.or(stat.site);
+            crate::println!(
+                "{:#18x} {:>5} {:>10} {:>12} {:>9} us {:>9} us  {}",
+                stat.lock,
+                match stat.kind {
+                    LockKind::Spin => "spin",
+                    LockKind::Mutex => "mutex",
+                    LockKind::RwLock => "rw",
+                },
+                stat.contentions,
+                stat.spins,
+                stat.max_wait / 1_000,
+                stat.max_hold / 1_000,
+                site.map_or(alloc::string::String::from("?"), |site| alloc::format!("{}:{}", site.file(), site.line()))
+            );
+        }
+        crate::println!("{} locks tracked, {} dropped", stats.len(), LOCK_STAT_DROPPED.value());
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = (argc, argv);
+        crate::println!("per-lock statistics need a debug build");
+    }
+    0
+}
+
+crate::static_command!("lockstat", "lock contention statistics, or lockstat reset", cmd_lockstat);
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contended_records() {
+        let lock = 0x1000_0040;
+        contended(lock, LockKind::Spin, 7, 500, Location::caller());
+        contended(lock, LockKind::Spin, 3, 200, Location::caller());
+
+        let stat = find(lock).unwrap();
+        assert_eq!(stat.contentions.load(Ordering::Relaxed), 2);
+        assert_eq!(stat.spins.load(Ordering::Relaxed), 10);
+        assert_eq!(stat.max_wait.load(Ordering::Relaxed), 500);
+        assert_eq!(LockKind::from_raw(stat.kind.load(Ordering::Relaxed)), LockKind::Spin);
+    }
+
+    #[test]
+    fn test_short_hold_untracked() {
+        let lock = 0x1000_0080;
+        record_hold(lock, LockKind::Mutex, LONG_HOLD_NS - 1, Location::caller());
+        assert!(find(lock).is_none());
+    }
+
+    #[test]
+    fn test_long_hold_tracked() {
+        let lock = 0x1000_00c0;
+        record_hold(lock, LockKind::Mutex, 2 * LONG_HOLD_NS, Location::caller());
+
+        // Once tracked, shorter holds count too
+        record_hold(lock, LockKind::Mutex, 10, Location::caller());
+
+        let stat = find(lock).unwrap();
+        assert_eq!(stat.max_hold.load(Ordering::Relaxed), 2 * LONG_HOLD_NS);
+        assert!(!stat.max_hold_site.load(Ordering::Relaxed).is_null());
+        assert_eq!(stat.contentions.load(Ordering::Relaxed), 0);
+    }
+}
@@ -15,6 +15,16 @@
 //! - **Mutex**: Mutual exclusion lock with thread ownership tracking
 //! - **Event**: Single-signal synchronization primitive
 //! - **Wait Queue**: Queue for threads waiting on a condition
+//! - **SpinLock**: Spinlock; holding one disables preemption
+//! - **SpinLockIrqSave**: Spinlock that also disables interrupts, for data
+//!   shared with interrupt handlers
+//! - **RwLock**: Reader-writer spinlock
+//! - **SeqLock**: Sequence lock for small data read far more often than
+//!   written; readers never wait or write
+//!
+//! All locks hand out RAII guards. Contention and hold times are tracked
+//! by [`lockstat`]; in debug builds a guard can release its lock poisoned
+//! (see [`poison`]).
 //!
 //! # Design
 //!
@@ -26,9 +36,15 @@ pub mod mutex;
 pub mod event;
 pub mod wait_queue;
 pub mod spin;
+pub mod rwlock;
+pub mod seqlock;
+pub mod lockstat;
+pub mod poison;
 
 // Re-exports
 pub use mutex::*;
 pub use event::*;
 pub use wait_queue::*;
 pub use spin::*;
+pub use rwlock::*;
+pub use seqlock::*;
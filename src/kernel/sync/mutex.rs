@@ -16,6 +16,15 @@
 //! - **Fair scheduling**: Waiters are woken in FIFO order
 //! - **Priority inheritance**: (TODO) Owner inherits priority of waiters
 //! - **Deadlock detection**: (TODO) Detect if a thread tries to lock its own mutex
+//! - **Atomic context**: Locking may block, so it is a bug with preemption
+//!   disabled or in an interrupt handler (see [`preempt::might_block`])
+//! - **Statistics**: Contention and, in debug builds, hold times are
+//!   recorded in [`lockstat`](super::lockstat)
+//! - **Poisoning**: A guard can release the mutex poisoned (see
+//!   [`poison`](super::poison))
+//! - **Lock ordering**: With the `lockdep` feature, acquisitions are
+//!   checked for lock order inversions (see
+//!   [`lockdep`](crate::kernel::lib::lockdep))
 //!
 //! # Usage
 //!
@@ -33,10 +42,16 @@
 //! ```
 
 
+use super::lockstat::{self, LockKind};
+use super::poison::Poison;
+use crate::kernel::lib::lockdep;
+use crate::kernel::preempt;
 use crate::kernel::thread::{Thread, ThreadId};
+use crate::kernel::timer;
 use crate::rustux::types::*;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use core::cell::UnsafeCell;
+use core::panic::Location;
 use crate::kernel::sync::spin::SpinMutex as SpinMutex;
 use alloc::vec::Vec;
 
@@ -65,6 +80,14 @@ pub struct Mutex<T> {
 
     /// Wait queue of threads waiting for this mutex
     waiters: SpinMutex<Vec<ThreadId>>,
+
+    /// When and where the owner took the mutex
+    #[cfg(debug_assertions)]
+    holder: lockstat::Holder,
+
+    poison: Poison,
+
+    class: lockdep::Class,
 }
 
 /// Magic number for mutex validation
@@ -72,6 +95,7 @@ const MUTEX_MAGIC: u32 = 0x4D555478; // "MUTx" in hex
 
 impl<T> Mutex<T> {
     /// Create a new mutex
+    #[track_caller]
     pub const fn new(data: T) -> Self {
         Self {
             data: UnsafeCell::new(data),
@@ -79,6 +103,10 @@ impl<T> Mutex<T> {
             has_waiters: AtomicBool::new(false),
             magic: MUTEX_MAGIC,
             waiters: SpinMutex::new(Vec::new()),
+            #[cfg(debug_assertions)]
+            holder: lockstat::Holder::new(),
+            poison: Poison::new(),
+            class: lockdep::Class::new(),
         }
     }
 
@@ -107,35 +135,55 @@ impl<T> Mutex<T> {
     /// Acquire the mutex
     ///
     /// Blocks the current thread until the mutex is available.
+    #[track_caller]
     pub fn lock(&self) -> MutexGuard<T> {
         self.validate();
+        self.poison.check();
+        preempt::might_block();
+        self.class.acquire(self as *const _ as usize, LockKind::Mutex, false);
 
         let current_tid = ThreadId::current(); // This would need to be implemented
 
         // Fast path: try to acquire if unlocked
         if self.try_lock_fast(current_tid) {
-            return MutexGuard::new(self);
+            return self.acquired(Location::caller());
         }
 
         // Slow path: need to block
+        let start = timer::current_time();
         self.lock_contended(current_tid);
-        MutexGuard::new(self)
+        let wait = timer::current_time().saturating_sub(start);
+        lockstat::contended(self as *const _ as usize, LockKind::Mutex, 0, wait, Location::caller());
+        self.acquired(Location::caller())
     }
 
     /// Try to acquire the mutex without blocking
     ///
     /// Returns Some(guard) if acquired, None if already locked.
+    #[track_caller]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
         self.validate();
+        self.poison.check();
 
         let current_tid = ThreadId::current();
         if self.try_lock_fast(current_tid) {
-            Some(MutexGuard::new(self))
+            self.class.acquire(self as *const _ as usize, LockKind::Mutex, true);
+            Some(self.acquired(Location::caller()))
         } else {
             None
         }
     }
 
+    /// The mutex was just taken at `site`
+    #[inline]
+    fn acquired(&self, site: &'static Location<'static>) -> MutexGuard<T> {
+        #[cfg(debug_assertions)]
+        self.holder.acquired(site);
+        #[cfg(not(debug_assertions))]
+        let _ = site;
+        MutexGuard::new(self)
+    }
+
     /// Get raw access to the data (without locking)
     ///
     /// # Safety
@@ -167,6 +215,10 @@ impl<T> Mutex<T> {
             panic!("mutex_release: thread tried to release mutex it doesn't own");
         }
 
+        #[cfg(debug_assertions)]
+        self.holder.released(self as *const _ as usize, LockKind::Mutex);
+        self.class.release(self as *const _ as usize);
+
         // Fast path: no waiters
         if !self.has_waiters.load(Ordering::Acquire) {
             self.owner.store(0, Ordering::Release);
@@ -177,6 +229,18 @@ impl<T> Mutex<T> {
         self.unlock_contended(current_tid);
     }
 
+    /// Whether a guard released the mutex poisoned
+    ///
+    /// Always false in release builds.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.is_poisoned()
+    }
+
+    /// Mark the data repaired after a guard poisoned the mutex
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
     /// Check if the mutex is currently locked
     pub fn is_locked(&self) -> bool {
         self.owner.load(Ordering::Acquire) != 0
@@ -289,6 +353,12 @@ impl<'a, T> MutexGuard<'a, T> {
         // SAFETY: We have exclusive access through the guard
         unsafe { &mut *self.mutex.data.get() }
     }
+
+    /// Release the mutex, marking the data inconsistent
+    #[track_caller]
+    pub fn poison(self) {
+        self.mutex.poison.poison(Location::caller());
+    }
 }
 
 impl<'a, T> core::ops::Deref for MutexGuard<'a, T> {
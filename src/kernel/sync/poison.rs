@@ -0,0 +1,142 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Lock Poisoning
+//!
+//! The kernel panics with `panic = "abort"`, so a lock is never released by
+//! unwinding and can't poison itself the way `std` locks do. Instead a
+//! holder that leaves the protected data inconsistent, such as a bail-out
+//! halfway through an update, releases the lock with the guard's
+//! `poison()`. In debug builds, taking a poisoned lock panics naming the
+//! site that poisoned it, until someone who has repaired the data calls
+//! `clear_poison()`.
+//!
+//! In release builds [`Poison`] is empty and poisoning does nothing beyond
+//! releasing the lock.
+
+use core::panic::Location;
+
+#[cfg(debug_assertions)]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// A lock's poison flag
+#[cfg(debug_assertions)]
+pub struct Poison {
+    /// Where the lock was poisoned, null if it isn't
+    site: AtomicPtr<Location<'static>>,
+}
+
+/// A lock's poison flag (release builds: never poisoned)
+#[cfg(not(debug_assertions))]
+pub struct Poison;
+
+#[cfg(debug_assertions)]
+impl Poison {
+    /// Not poisoned
+    pub const fn new() -> Self {
+        Self { site: AtomicPtr::new(core::ptr::null_mut()) }
+    }
+
+    /// Mark poisoned by `site`
+    pub fn poison(&self, site: &'static Location<'static>) {
+        self.site.store(site as *const _ as *mut _, Ordering::Release);
+    }
+
+    /// Where the lock was poisoned, if it is
+    pub fn site(&self) -> Option<&'static Location<'static>> {
+        // SAFETY: only ever set from a `&'static Location`
+        unsafe { (self.site.load(Ordering::Acquire) as *const Location<'static>).as_ref() }
+    }
+
+    /// Whether the lock is poisoned
+    pub fn is_poisoned(&self) -> bool {
+        self.site().is_some()
+    }
+
+    /// Mark the data repaired
+    pub fn clear(&self) {
+        self.site.store(core::ptr::null_mut(), Ordering::Release);
+    }
+
+    /// Panic if poisoned; called before taking the lock
+    #[inline]
+    #[track_caller]
+    pub fn check(&self) {
+        if let Some(site) = self.site() {
+            panic!("lock taken at {} was poisoned at {}", Location::caller(), site);
+        }
+    }
+}
+
+#[cfg(not(debug_assertions))]
+impl Poison {
+    /// Not poisoned
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Mark poisoned by `site`
+    #[inline]
+    pub fn poison(&self, _site: &'static Location<'static>) {}
+
+    /// Where the lock was poisoned, if it is
+    #[inline]
+    pub fn site(&self) -> Option<&'static Location<'static>> {
+        None
+    }
+
+    /// Whether the lock is poisoned
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Mark the data repaired
+    #[inline]
+    pub fn clear(&self) {}
+
+    /// Panic if poisoned; called before taking the lock
+    #[inline]
+    pub fn check(&self) {}
+}
+
+impl Default for Poison {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poison_and_clear() {
+        let poison = Poison::new();
+        poison.check();
+        assert!(!poison.is_poisoned());
+
+        let site = Location::caller();
+        poison.poison(site);
+        assert_eq!(poison.site(), Some(site));
+
+        poison.clear();
+        assert!(!poison.is_poisoned());
+        poison.check();
+    }
+
+    #[test]
+    #[should_panic(expected = "was poisoned at")]
+    fn test_poisoned_check_panics() {
+        let poison = Poison::new();
+        poison.poison(Location::caller());
+        poison.check();
+    }
+}
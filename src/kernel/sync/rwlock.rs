@@ -0,0 +1,324 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Reader-Writer Spinlock
+//!
+//! Any number of readers, or one writer. Like [`SpinLock`](super::SpinLock)
+//! it spins rather than blocks and disables preemption while held, so it
+//! is for short critical sections that are mostly reads.
+//!
+//! # Design
+//!
+//! - **State word**: One `AtomicU32` holds the reader count, a writer bit
+//!   and a writer-waiting bit.
+//! - **Writer preference**: A waiting writer sets the waiting bit, which
+//!   keeps new readers out until it gets in, so a steady stream of readers
+//!   can't starve it.
+//! - **Debug checks**: Write guards can release the lock poisoned (see
+//!   [`poison`](super::poison)); contention is recorded in
+//!   [`lockstat`](super::lockstat). With the `lockdep` feature, reads and
+//!   writes alike are checked for lock order inversions.
+//!
+//! # Usage
+//!
+//! ```rust
+//! static ROUTES: RwLock<Vec<Route>> = RwLock::new(Vec::new());
+//!
+//! let route = ROUTES.read().iter().find(|r| r.matches(addr)).copied();
+//! ROUTES.write().push(new_route);
+//! ```
+
+use super::lockstat::{self, LockKind};
+use super::poison::Poison;
+use crate::kernel::lib::lockdep;
+use crate::kernel::preempt;
+use crate::kernel::timer;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// A writer holds the lock
+const WRITER: u32 = 1 << 31;
+
+/// A writer is waiting; no new readers
+const WRITER_WAITING: u32 = 1 << 30;
+
+/// Reader count
+const READERS: u32 = WRITER_WAITING - 1;
+
+/// ============================================================================
+/// RwLock
+/// ============================================================================
+
+/// A reader-writer spinlock
+pub struct RwLock<T> {
+    state: AtomicU32,
+    poison: Poison,
+    class: lockdep::Class,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Create a new reader-writer lock
+    #[track_caller]
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            poison: Poison::new(),
+            class: lockdep::Class::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Acquire shared access, spinning while a writer holds or waits for
+    /// the lock
+    #[track_caller]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.poison.check();
+        self.class.acquire(self.addr(), LockKind::RwLock, false);
+        preempt::disable();
+        if !self.try_read_raw() {
+            let start = timer::current_time();
+            let mut spins = 0;
+            while !self.try_read_raw() {
+                core::hint::spin_loop();
+                spins += 1;
+            }
+            self.contended(spins, start);
+        }
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Acquire exclusive access, spinning until readers and any other
+    /// writer are gone
+    #[track_caller]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.poison.check();
+        self.class.acquire(self.addr(), LockKind::RwLock, false);
+        preempt::disable();
+        if !self.try_write_raw() {
+            let start = timer::current_time();
+            let mut spins = 0;
+            loop {
+                let state = self.state.load(Ordering::Relaxed);
+                if state & !WRITER_WAITING == 0 {
+                    // Taking the lock also clears the waiting bit; other
+                    // waiting writers set it again
+                    if self
+                        .state
+                        .compare_exchange_weak(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                        .is_ok()
+                    {
+                        break;
+                    }
+                } else if state & WRITER_WAITING == 0 {
+                    self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+                }
+                core::hint::spin_loop();
+                spins += 1;
+            }
+            self.contended(spins, start);
+        }
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Try to acquire shared access without spinning
+    #[track_caller]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.poison.check();
+        preempt::disable();
+        if self.try_read_raw() {
+            self.class.acquire(self.addr(), LockKind::RwLock, true);
+            Some(RwLockReadGuard { lock: self })
+        } else {
+            preempt::enable();
+            None
+        }
+    }
+
+    /// Try to acquire exclusive access without spinning
+    #[track_caller]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.poison.check();
+        preempt::disable();
+        if self.try_write_raw() {
+            self.class.acquire(self.addr(), LockKind::RwLock, true);
+            Some(RwLockWriteGuard { lock: self })
+        } else {
+            preempt::enable();
+            None
+        }
+    }
+
+    /// Whether a write guard released the lock poisoned
+    ///
+    /// Always false in release builds.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.is_poisoned()
+    }
+
+    /// Mark the data repaired after a write guard poisoned the lock
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    /// Number of readers holding the lock
+    pub fn reader_count(&self) -> u32 {
+        self.state.load(Ordering::Relaxed) & READERS
+    }
+
+    /// Whether a writer holds the lock
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Get a raw pointer to the inner data
+    ///
+    /// # Safety
+    ///
+    /// No synchronization; the caller must ensure proper access.
+    pub unsafe fn as_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    fn addr(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    fn try_read_raw(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | WRITER_WAITING) != 0 {
+            return false;
+        }
+        debug_assert!(state & READERS != READERS, "too many readers");
+        self.state.compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    fn try_write_raw(&self) -> bool {
+        self.state.compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    #[cold]
+    #[track_caller]
+    fn contended(&self, spins: u64, start: u64) {
+        let wait = timer::current_time().saturating_sub(start);
+        lockstat::contended(self.addr(), LockKind::RwLock, spins, wait, Location::caller());
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// ============================================================================
+/// Guards
+/// ============================================================================
+
+/// RAII guard for shared access to a RwLock
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.class.release(self.lock.addr());
+        self.lock.state.fetch_sub(1, Ordering::Release);
+        preempt::enable();
+    }
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+/// RAII guard for exclusive access to a RwLock
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> RwLockWriteGuard<'a, T> {
+    /// Release the lock, marking the data inconsistent
+    #[track_caller]
+    pub fn poison(self) {
+        self.lock.poison.poison(Location::caller());
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // Leave a waiting writer's bit in place
+        self.lock.class.release(self.lock.addr());
+        self.lock.state.fetch_and(!WRITER, Ordering::Release);
+        preempt::enable();
+    }
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readers_share() {
+        let lock = RwLock::new(5u32);
+        let a = lock.read();
+        let b = lock.read();
+        assert_eq!(*a + *b, 10);
+        assert_eq!(lock.reader_count(), 2);
+        assert!(lock.try_write().is_none());
+        drop(a);
+        drop(b);
+        assert_eq!(lock.reader_count(), 0);
+    }
+
+    #[test]
+    fn test_writer_excludes() {
+        let lock = RwLock::new(0u32);
+        {
+            let mut guard = lock.write();
+            *guard = 7;
+            assert!(lock.is_write_locked());
+            assert!(lock.try_read().is_none());
+            assert!(lock.try_write().is_none());
+        }
+        assert!(!lock.is_write_locked());
+        assert_eq!(*lock.read(), 7);
+    }
+
+    #[test]
+    fn test_waiting_writer_blocks_readers() {
+        let lock = RwLock::new(0u32);
+        let _reader = lock.read();
+        lock.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+        assert!(lock.try_read().is_none());
+        assert_eq!(lock.reader_count(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Sequence Lock
+//!
+//! For small, `Copy` data read far more often than written, such as time
+//! values. Readers never write shared memory and never wait for each
+//! other; they copy the data and retry if a writer was active meanwhile.
+//!
+//! # Design
+//!
+//! - **Sequence**: Even while the data is stable, odd while a writer is
+//!   changing it. A read is good if the sequence was even and unchanged
+//!   across the copy.
+//! - **Writers**: Serialize on the sequence itself, making it odd with a
+//!   compare-exchange, and run with interrupts and preemption disabled so
+//!   a reader on the same CPU can't spin on an odd sequence forever.
+//! - **Layout**: `#[repr(C)]`, sequence first. A `SeqLock` can sit in a
+//!   page mapped read-only into userspace, whose readers follow the same
+//!   protocol (see [`timepage`](crate::kernel::lib::timepage)).
+//!
+//! Like every seqlock, readers copy the data while a writer may be
+//! changing it, and throw the copy away if so; the copy is volatile so the
+//! compiler can't assume it is stable.
+//!
+//! # Usage
+//!
+//! ```rust
+//! static OFFSET: SeqLock<(u64, u64)> = SeqLock::new((0, 0));
+//!
+//! let (base, scale) = OFFSET.read();
+//! OFFSET.write(|v| *v = (new_base, new_scale));
+//! ```
+
+use crate::kernel::arch;
+use crate::kernel::preempt;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+
+/// A sequence lock around `T`
+#[repr(C)]
+pub struct SeqLock<T> {
+    seq: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    /// Create a new sequence lock
+    pub const fn new(data: T) -> Self {
+        Self { seq: AtomicU32::new(0), data: UnsafeCell::new(data) }
+    }
+
+    /// Copy the data out, retrying while a writer is active
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Copy the data out, or `None` if a writer was active
+    pub fn try_read(&self) -> Option<T> {
+        let start = self.seq.load(Ordering::Acquire);
+        if start & 1 != 0 {
+            return None;
+        }
+        let value = unsafe { core::ptr::read_volatile(self.data.get()) };
+        fence(Ordering::Acquire);
+        (self.seq.load(Ordering::Relaxed) == start).then_some(value)
+    }
+
+    /// Change the data
+    ///
+    /// Runs `f` with interrupts and preemption disabled, so keep it short.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let state = arch::arch_interrupt_save();
+        preempt::disable();
+
+        loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self.seq.compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed).is_ok()
+            {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+
+        let result = f(unsafe { &mut *self.data.get() });
+        self.seq.fetch_add(1, Ordering::Release);
+
+        preempt::enable();
+        unsafe { arch::arch_interrupt_restore(state) };
+        result
+    }
+
+    /// Current sequence number; advances by 2 per write
+    pub fn sequence(&self) -> u32 {
+        self.seq.load(Ordering::Acquire)
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_sees_write() {
+        let lock = SeqLock::new((1u64, 2u64));
+        assert_eq!(lock.read(), (1, 2));
+        assert_eq!(lock.write(|v| core::mem::replace(v, (3, 4))), (1, 2));
+        assert_eq!(lock.read(), (3, 4));
+        assert_eq!(lock.sequence(), 2);
+    }
+
+    #[test]
+    fn test_read_fails_during_write() {
+        let lock = SeqLock::new(0u64);
+        lock.seq.store(1, Ordering::Relaxed);
+        assert_eq!(lock.try_read(), None);
+        lock.seq.store(2, Ordering::Relaxed);
+        assert_eq!(lock.try_read(), Some(0));
+    }
+}
@@ -6,17 +6,79 @@
 
 //! Spinlock Implementation
 //!
-//! This module provides a simple spinlock for kernel use.
+//! This module provides spinlocks for kernel use.
 //! Spinlocks are used when the expected wait time is very short.
+//!
+//! # Types
+//!
+//! - [`SpinLock`] (also [`SpinMutex`]): Spinlock for data never touched
+//!   from interrupt handlers
+//! - [`SpinLockIrqSave`]: Spinlock that also disables interrupts while
+//!   held, for data shared with interrupt handlers. An interrupt taking a
+//!   plain spinlock its CPU already holds would spin forever.
+//!
+//! # Design
+//!
+//! - **Preemption**: Holding a spinlock disables preemption on the CPU, so
+//!   blocking while holding one is caught by [`preempt::might_block`].
+//! - **Statistics**: Contention and, in debug builds, hold times are
+//!   recorded in [`lockstat`](super::lockstat).
+//! - **Debug checks**: In debug builds a spinlock remembers the CPU holding
+//!   it, and taking it again on that CPU panics instead of deadlocking.
+//!   Guards can release a lock poisoned (see [`poison`](super::poison)).
+//! - **Lock ordering**: With the `lockdep` feature, acquisitions are
+//!   checked against the order locks were taken in before (see
+//!   [`lockdep`](crate::kernel::lib::lockdep)).
+//!
+//! # Usage
+//!
+//! ```rust
+//! static TICKS: SpinLockIrqSave<u64> = SpinLockIrqSave::new(0);
+//!
+//! *TICKS.lock() += 1;
+//! ```
 
 
+use super::lockstat::{self, LockKind};
+use super::poison::Poison;
+use crate::kernel::arch;
+use crate::kernel::lib::lockdep;
+use crate::kernel::preempt;
+use crate::kernel::timer;
 use core::sync::atomic::{AtomicBool, Ordering};
 use core::cell::UnsafeCell;
+use core::mem::ManuallyDrop;
 use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+
+#[cfg(debug_assertions)]
+use crate::kernel::{irq, percpu};
+#[cfg(debug_assertions)]
+use core::sync::atomic::AtomicU32;
+
+/// `owner_cpu` of an unheld lock
+#[cfg(debug_assertions)]
+const NO_OWNER: u32 = u32::MAX;
+
+/// This CPU's number, or `None` before per-CPU data is set up
+#[cfg(debug_assertions)]
+fn this_cpu() -> Option<u32> {
+    (percpu::num_cpus() != 0).then(percpu::current_cpu_num)
+}
+
+/// ============================================================================
+/// SpinMutex
+/// ============================================================================
 
 /// A simple spinlock
 pub struct SpinMutex<T> {
     locked: AtomicBool,
+    #[cfg(debug_assertions)]
+    holder: lockstat::Holder,
+    #[cfg(debug_assertions)]
+    owner_cpu: AtomicU32,
+    poison: Poison,
+    class: lockdep::Class,
     data: UnsafeCell<T>,
 }
 
@@ -25,31 +87,100 @@ unsafe impl<T: Send> Sync for SpinMutex<T> {}
 
 impl<T> SpinMutex<T> {
     /// Create a new spinlock
+    #[track_caller]
     pub const fn new(data: T) -> Self {
         Self {
             locked: AtomicBool::new(false),
+            #[cfg(debug_assertions)]
+            holder: lockstat::Holder::new(),
+            #[cfg(debug_assertions)]
+            owner_cpu: AtomicU32::new(NO_OWNER),
+            poison: Poison::new(),
+            class: lockdep::Class::new(),
             data: UnsafeCell::new(data),
         }
     }
 
     /// Acquire the lock, spinning until it becomes available
+    #[track_caller]
     pub fn lock(&self) -> SpinMutexGuard<'_, T> {
-        while self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-            // Spin with pause to reduce bus contention
-            core::hint::spin_loop();
+        self.poison.check();
+        self.class.acquire(self as *const _ as usize, LockKind::Spin, false);
+        preempt::disable();
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            #[cfg(debug_assertions)]
+            self.check_recursion();
+            self.lock_contended(Location::caller());
         }
-        SpinMutexGuard { mutex: self }
+        self.acquired(Location::caller())
     }
 
     /// Try to acquire the lock without spinning
+    #[track_caller]
     pub fn try_lock(&self) -> Option<SpinMutexGuard<'_, T>> {
+        self.poison.check();
+        preempt::disable();
         if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok() {
-            Some(SpinMutexGuard { mutex: self })
+            self.class.acquire(self as *const _ as usize, LockKind::Spin, true);
+            Some(self.acquired(Location::caller()))
         } else {
+            preempt::enable();
             None
         }
     }
 
+    /// Whether a guard released the lock poisoned
+    ///
+    /// Always false in release builds.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.is_poisoned()
+    }
+
+    /// Mark the data repaired after a guard poisoned the lock
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    #[cold]
+    fn lock_contended(&self, site: &'static Location<'static>) {
+        let start = timer::current_time();
+        let mut spins = 0;
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            // Spin with pause to reduce bus contention
+            core::hint::spin_loop();
+            spins += 1;
+        }
+        let wait = timer::current_time().saturating_sub(start);
+        lockstat::contended(self as *const _ as usize, LockKind::Spin, spins, wait, site);
+    }
+
+    /// Panic if this CPU already holds the lock, which would never be
+    /// released
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    fn check_recursion(&self) {
+        let cpu = match this_cpu() {
+            Some(cpu) => cpu,
+            None => return,
+        };
+        if self.owner_cpu.load(Ordering::Relaxed) == cpu {
+            let hint = if irq::in_irq() { "; locks taken in interrupt handlers must be SpinLockIrqSave" } else { "" };
+            panic!("spinlock taken at {} is already held on CPU {}{}", Location::caller(), cpu, hint);
+        }
+    }
+
+    #[inline]
+    fn acquired(&self, site: &'static Location<'static>) -> SpinMutexGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        {
+            self.holder.acquired(site);
+            self.owner_cpu.store(this_cpu().unwrap_or(NO_OWNER), Ordering::Relaxed);
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = site;
+        SpinMutexGuard { mutex: self }
+    }
+
     /// Get a raw pointer to the inner data
     ///
     /// # Safety
@@ -66,9 +197,24 @@ pub struct SpinMutexGuard<'a, T> {
     mutex: &'a SpinMutex<T>,
 }
 
+impl<'a, T> SpinMutexGuard<'a, T> {
+    /// Release the lock, marking the data inconsistent
+    #[track_caller]
+    pub fn poison(self) {
+        self.mutex.poison.poison(Location::caller());
+    }
+}
+
 impl<'a, T> Drop for SpinMutexGuard<'a, T> {
     fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            self.mutex.holder.released(self.mutex as *const _ as usize, LockKind::Spin);
+            self.mutex.owner_cpu.store(NO_OWNER, Ordering::Relaxed);
+        }
+        self.mutex.class.release(self.mutex as *const _ as usize);
         self.mutex.locked.store(false, Ordering::Release);
+        preempt::enable();
     }
 }
 
@@ -87,3 +233,144 @@ impl<'a, T> DerefMut for SpinMutexGuard<'a, T> {
 
 /// Type alias for SpinMutex as SpinLock for compatibility
 pub type SpinLock<T> = SpinMutex<T>;
+
+/// ============================================================================
+/// SpinLockIrqSave
+/// ============================================================================
+
+/// A spinlock that disables interrupts on the CPU while held
+///
+/// Interrupts are disabled before spinning and restored to their previous
+/// state on release, so these nest with each other and with interrupt
+/// handlers.
+pub struct SpinLockIrqSave<T> {
+    inner: SpinMutex<T>,
+}
+
+impl<T> SpinLockIrqSave<T> {
+    /// Create a new spinlock
+    #[track_caller]
+    pub const fn new(data: T) -> Self {
+        Self { inner: SpinMutex::new(data) }
+    }
+
+    /// Disable interrupts and acquire the lock
+    #[track_caller]
+    pub fn lock(&self) -> SpinLockIrqSaveGuard<'_, T> {
+        let state = arch::arch_interrupt_save();
+        SpinLockIrqSaveGuard { guard: ManuallyDrop::new(self.inner.lock()), state }
+    }
+
+    /// Try to acquire the lock without spinning
+    ///
+    /// Leaves the interrupt state alone on failure.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<SpinLockIrqSaveGuard<'_, T>> {
+        let state = arch::arch_interrupt_save();
+        match self.inner.try_lock() {
+            Some(guard) => Some(SpinLockIrqSaveGuard { guard: ManuallyDrop::new(guard), state }),
+            None => {
+                unsafe { arch::arch_interrupt_restore(state) };
+                None
+            }
+        }
+    }
+
+    /// Whether a guard released the lock poisoned
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    /// Mark the data repaired after a guard poisoned the lock
+    pub fn clear_poison(&self) {
+        self.inner.clear_poison();
+    }
+
+    /// Get a raw pointer to the inner data
+    ///
+    /// # Safety
+    ///
+    /// As for [`SpinMutex::as_ptr`].
+    pub unsafe fn as_ptr(&self) -> *mut T {
+        self.inner.as_ptr()
+    }
+}
+
+/// RAII guard for a SpinLockIrqSave
+pub struct SpinLockIrqSaveGuard<'a, T> {
+    guard: ManuallyDrop<SpinMutexGuard<'a, T>>,
+
+    /// Interrupt state from before the lock was taken
+    state: u64,
+}
+
+impl<'a, T> SpinLockIrqSaveGuard<'a, T> {
+    /// Release the lock, marking the data inconsistent
+    #[track_caller]
+    pub fn poison(self) {
+        self.guard.mutex.poison.poison(Location::caller());
+    }
+}
+
+impl<'a, T> Drop for SpinLockIrqSaveGuard<'a, T> {
+    fn drop(&mut self) {
+        // Release before interrupts can come back in
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+            arch::arch_interrupt_restore(self.state);
+        }
+    }
+}
+
+impl<'a, T> Deref for SpinLockIrqSaveGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for SpinLockIrqSaveGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_and_try_lock() {
+        let lock = SpinLock::new(1u32);
+        {
+            let mut guard = lock.lock();
+            *guard += 1;
+            assert!(lock.try_lock().is_none());
+        }
+        assert_eq!(*lock.try_lock().unwrap(), 2);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "was poisoned at")]
+    fn test_poisoned_lock_panics() {
+        let lock = SpinLock::new(0u32);
+        lock.lock().poison();
+        assert!(lock.is_poisoned());
+        let _guard = lock.lock();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_clear_poison() {
+        let lock = SpinLock::new(0u32);
+        lock.lock().poison();
+        lock.clear_poison();
+        assert!(!lock.is_poisoned());
+        assert_eq!(*lock.lock(), 0);
+    }
+}
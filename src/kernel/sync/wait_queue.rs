@@ -33,6 +33,7 @@
 //! ```
 
 
+use crate::kernel::preempt;
 use crate::kernel::thread::ThreadId;
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
@@ -116,8 +117,10 @@ impl WaitQueue {
     ///
     /// - `Ok(())` if woken successfully
     /// - `Err(RX_ERR_TIMED_OUT)` if deadline reached
+    #[track_caller]
     pub fn block(&self, deadline: u64) -> Result {
         self.validate();
+        preempt::might_block();
 
         // Get current thread (stub)
         let tid = ThreadId::current();
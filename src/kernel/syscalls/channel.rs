@@ -12,8 +12,12 @@
 //! # Syscalls Implemented
 //!
 //! - `rx_channel_create` - Create a channel pair
-//! - `rx_channel_write` - Write to a channel
+//! - `rx_channel_write` - Write to a channel, optionally gathering iovecs
 //! - `rx_channel_read` - Read from a channel
+//! - `rx_channel_write_etc` - Write with per-handle dispositions that move
+//!   or duplicate, type-check and reduce the rights of each handle
+//! - `rx_channel_read_etc` - Read with the type and rights of each handle
+//! - `rx_channel_call_etc` - Write a request and wait for its reply
 //!
 //! # Design
 //!
@@ -22,11 +26,18 @@
 //! - Handles are transferred with rights validation
 //! - FIFO ordering guaranteed
 //! - Bounded queue with backpressure
+//! - A caller lends its priority to the thread that reads its request
+//!   until that thread writes the reply, and gives its time slice to the
+//!   thread that last read from the peer
 
 
-use crate::kernel::object::channel::{self, Channel, ChannelId, Message, MAX_MSG_HANDLES, MAX_MSG_SIZE};
+use crate::kernel::object::channel::{self, CallInfo, Channel, ChannelId, Message, MAX_MSG_HANDLES, MAX_MSG_SIZE, TXID_SIZE};
+use crate::kernel::object::message_packet::{self, PacketUsage};
 use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
+use crate::kernel::sched;
 use crate::kernel::sync::Mutex;
+use crate::kernel::thread::{self, PRIORITY_DEFAULT, TID_INVALID};
+use crate::kernel::timer;
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::vm::layout::*;
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
@@ -39,7 +50,7 @@ use alloc::vec;
 
 // Import logging macros
 use crate::{log_debug, log_error, log_info};
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 
 /// ============================================================================
 /// Channel Registry
@@ -152,13 +163,10 @@ static CHANNEL_REGISTRY: Mutex<ChannelRegistry> = Mutex::new(ChannelRegistry::ne
 
 /// Get the current process's handle table
 ///
-/// This is a placeholder that returns NULL for now.
-/// In a real implementation, this would use thread-local storage
-/// or per-CPU data to get the current process.
+/// Uses the same table as the handle syscalls until processes are
+/// tracked per thread.
 fn current_process_handle_table() -> Option<&'static HandleTable> {
-    // TODO: Implement proper current process tracking
-    // For now, return None to indicate not implemented
-    None
+    Some(crate::kernel::thread::current_thread_handle_table())
 }
 
 /// Look up a channel from a handle value
@@ -178,7 +186,7 @@ fn lookup_channel_from_handle(
 
     // Get the handle from the table
     let handle = handle_table.get(handle_val)
-        .ok_or(RX_ERR_INVALID_ARGS)?;
+        .ok_or(RX_ERR_BAD_HANDLE)?;
 
     // Validate object type
     if handle.obj_type() != ObjectType::Channel {
@@ -199,13 +207,44 @@ fn lookup_channel_from_handle(
     Ok((channel, handle))
 }
 
-/// ============================================================================
-/// Channel Kernel Object Base
-/// ============================================================================
+/// The endpoint that messages written to `channel` are queued on
+fn peer_channel(channel: &Channel) -> Result<Arc<Channel>> {
+    let peer_id = (*channel.peer.lock()).ok_or(RX_ERR_PEER_CLOSED)?;
+    CHANNEL_REGISTRY.lock().get(peer_id).ok_or(RX_ERR_PEER_CLOSED)
+}
+
+/// Get the koid of a channel's peer
+///
+/// Returns `None` if `channel_id` is not a channel.
+pub fn peer_koid(channel_id: ChannelId) -> Option<u64> {
+    CHANNEL_REGISTRY.lock().get(channel_id).map(|channel| channel.peer_koid)
+}
+
+/// Create a channel pair and register both ends
+///
+/// Returns the IDs of the two ends, which are also their handle values.
+pub fn create_pair() -> Result<(ChannelId, ChannelId)> {
+    let (channel_a, channel_b) = Channel::create()?;
+    log_debug!("channel: created channels id_a={} id_b={}", channel_a.id, channel_b.id);
+
+    let id_a = CHANNEL_REGISTRY.lock().insert(Arc::new(channel_a))?;
+    let id_b = match CHANNEL_REGISTRY.lock().insert(Arc::new(channel_b)) {
+        Ok(id) => id,
+        Err(err) => {
+            CHANNEL_REGISTRY.lock().remove(id_a);
+            return Err(err);
+        }
+    };
+    Ok((id_a, id_b))
+}
 
-/// Create a kernel object base for a channel
-fn channel_to_kernel_base(channel: &Arc<Channel>) -> KernelObjectBase {
-    KernelObjectBase::new(ObjectType::Channel)
+/// Queue a message written by the kernel through `channel_id`
+///
+/// The message goes to the peer, as if a thread had written to
+/// `channel_id`; it carries no handles.
+pub fn write_kernel_message(channel_id: ChannelId, data: &[u8]) -> Result<usize> {
+    let channel = CHANNEL_REGISTRY.lock().get(channel_id).ok_or(RX_ERR_BAD_HANDLE)?;
+    peer_channel(&channel)?.write(data, Vec::new())
 }
 
 /// ============================================================================
@@ -232,50 +271,14 @@ pub fn sys_channel_create_impl(options: u32) -> SyscallRet {
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // Create the channel pair
-    let (channel_a, channel_b) = match Channel::create() {
-        Ok(channels) => channels,
+    let (id_a, id_b) = match create_pair() {
+        Ok(ids) => ids,
         Err(err) => {
             log_error!("sys_channel_create: failed to create channel: {:?}", err);
             return err_to_ret(err);
         }
     };
 
-    log_debug!("sys_channel_create: created channels id_a={} id_b={}",
-        channel_a.id, channel_b.id);
-
-    // Wrap in Arc for registry
-    let channel_a_arc = Arc::new(channel_a);
-    let channel_b_arc = Arc::new(channel_b);
-
-    // Insert into channel registry
-    let id_a = match CHANNEL_REGISTRY.lock().insert(channel_a_arc.clone()) {
-        Ok(id) => id,
-        Err(err) => {
-            log_error!("sys_channel_create: failed to insert channel_a: {:?}", err);
-            return err_to_ret(err);
-        }
-    };
-
-    let id_b = match CHANNEL_REGISTRY.lock().insert(channel_b_arc.clone()) {
-        Ok(id) => id,
-        Err(err) => {
-            log_error!("sys_channel_create: failed to insert channel_b: {:?}", err);
-            // Cleanup channel_a
-            CHANNEL_REGISTRY.lock().remove(id_a);
-            return err_to_ret(err);
-        }
-    };
-
-    // Create kernel object bases
-    let base_a = channel_to_kernel_base(&channel_a_arc);
-    let base_b = channel_to_kernel_base(&channel_b_arc);
-
-    // Create handles with default rights (READ | WRITE)
-    let rights = Rights::READ | Rights::WRITE;
-    let handle_a = Handle::new(&base_a as *const KernelObjectBase, rights);
-    let handle_b = Handle::new(&base_b as *const KernelObjectBase, rights);
-
     // TODO: Add handles to current process's handle table
     // For now, return the channel IDs as the handle values
     let handle_value_a = id_a as u32;
@@ -290,6 +293,228 @@ pub fn sys_channel_create_impl(options: u32) -> SyscallRet {
     ok_to_ret(packed as usize)
 }
 
+/// ============================================================================
+/// Syscall: Channel Message Layout
+/// ============================================================================
+
+/// `rx_channel_write` option: `user_data` is an array of `data_size`
+/// `ChannelIovec`s to gather the message from
+pub const CHANNEL_WRITE_USE_IOVEC: u32 = 0x02;
+
+/// `rx_channel_read` option: drop a message that doesn't fit
+pub const CHANNEL_READ_MAY_DISCARD: u32 = 0x01;
+
+/// Maximum iovecs in one write
+pub const MAX_MSG_IOVECS: usize = 8192;
+
+/// Handle disposition: move the handle out of the sender's table
+pub const HANDLE_OP_MOVE: u32 = 0;
+
+/// Handle disposition: send a duplicate, keeping the sender's handle
+pub const HANDLE_OP_DUPLICATE: u32 = 1;
+
+/// One piece of a gathered message
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelIovec {
+    /// User address of the bytes
+    pub buffer: u64,
+
+    /// Number of bytes
+    pub capacity: u32,
+
+    /// Must be 0
+    pub reserved: u32,
+}
+
+/// How `rx_channel_write_etc` sends one handle
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandleDisposition {
+    /// `HANDLE_OP_MOVE` or `HANDLE_OP_DUPLICATE`
+    pub operation: u32,
+
+    /// Handle value in the sender's table
+    pub handle: u32,
+
+    /// Expected object type, or 0 for any
+    pub obj_type: u32,
+
+    /// Rights the receiver gets, a subset of the handle's, or
+    /// `Rights::SAME_RIGHTS`
+    pub rights: u32,
+
+    /// Written back: the status of this handle
+    pub result: Status,
+}
+
+/// A handle received by `rx_channel_read_etc`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandleInfo {
+    /// Handle value in the receiver's table
+    pub handle: u32,
+
+    /// Object type
+    pub obj_type: u32,
+
+    /// Rights
+    pub rights: u32,
+
+    /// Reserved
+    pub unused: u32,
+}
+
+/// Copy a message's bytes from user space, gathering iovecs if asked to
+fn copy_message_data(options: u32, user_data: usize, data_size: usize) -> Result<Vec<u8>> {
+    if options & CHANNEL_WRITE_USE_IOVEC == 0 {
+        if data_size > MAX_MSG_SIZE {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+        let mut data = vec![0u8; data_size];
+        if data_size > 0 {
+            unsafe { copy_from_user(data.as_mut_ptr(), UserPtr::new(user_data), data_size)? };
+        }
+        return Ok(data);
+    }
+
+    if data_size > MAX_MSG_IOVECS {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+    let mut iovecs = vec![ChannelIovec::default(); data_size];
+    if data_size > 0 {
+        let len = data_size * core::mem::size_of::<ChannelIovec>();
+        unsafe { copy_from_user(iovecs.as_mut_ptr() as *mut u8, UserPtr::new(user_data), len)? };
+    }
+
+    let mut total = 0usize;
+    for iovec in &iovecs {
+        if iovec.reserved != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        total += iovec.capacity as usize;
+        if total > MAX_MSG_SIZE {
+            return Err(RX_ERR_OUT_OF_RANGE);
+        }
+    }
+
+    let mut data = vec![0u8; total];
+    let mut offset = 0;
+    for iovec in iovecs.iter().filter(|iovec| iovec.capacity != 0) {
+        let len = iovec.capacity as usize;
+        unsafe { copy_from_user(data[offset..].as_mut_ptr(), UserPtr::new(iovec.buffer as usize), len)? };
+        offset += len;
+    }
+    Ok(data)
+}
+
+/// Check one disposition against the sender's handle
+///
+/// Returns the rights the receiver gets.
+fn check_disposition(disposition: &HandleDisposition, handle: &Handle, channel: &Handle) -> Result<Rights> {
+    if disposition.operation != HANDLE_OP_MOVE && disposition.operation != HANDLE_OP_DUPLICATE {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    // A channel can't carry a handle to itself
+    if handle.base == channel.base {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    if disposition.obj_type != 0 && disposition.obj_type != handle.obj_type().into_raw() {
+        return Err(RX_ERR_WRONG_TYPE);
+    }
+
+    handle.require(Rights::TRANSFER).map_err(|_| RX_ERR_ACCESS_DENIED)?;
+    if disposition.operation == HANDLE_OP_DUPLICATE {
+        handle.require(Rights::DUPLICATE).map_err(|_| RX_ERR_ACCESS_DENIED)?;
+    }
+
+    let rights = Rights::from_raw(disposition.rights);
+    if rights.contains(Rights::SAME_RIGHTS) {
+        return Ok(handle.rights());
+    }
+    if !handle.rights().contains(rights) {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    Ok(rights)
+}
+
+/// Turn the sender's handles into the handles a message carries
+///
+/// Every disposition's `result` is filled in. Moved handles leave the
+/// sender's table whether or not the others succeed, as a write always
+/// consumes them.
+fn take_handles(dispositions: &mut [HandleDisposition], channel: &Handle) -> Result<Vec<Handle>> {
+    let table = current_process_handle_table().ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    // Check everything before touching the table; a handle may only be
+    // listed once
+    let mut first_err = None;
+    let mut rights = Vec::with_capacity(dispositions.len());
+    for i in 0..dispositions.len() {
+        let value = dispositions[i].handle;
+        let result = match table.get(value) {
+            _ if dispositions[..i].iter().any(|d| d.handle == value) => Err(RX_ERR_BAD_HANDLE),
+            Some(handle) => check_disposition(&dispositions[i], &handle, channel),
+            None => Err(RX_ERR_BAD_HANDLE),
+        };
+        dispositions[i].result = result.err().unwrap_or(RX_OK);
+        first_err = first_err.or(result.err());
+        rights.push(result.unwrap_or(Rights::NONE));
+    }
+
+    if let Some(err) = first_err {
+        discard_handles(dispositions);
+        return Err(err);
+    }
+
+    let mut handles = Vec::with_capacity(dispositions.len());
+    for (disposition, rights) in dispositions.iter().zip(rights) {
+        let handle = if disposition.operation == HANDLE_OP_MOVE {
+            let mut handle = table.take(disposition.handle)?;
+            handle.rights = rights;
+            handle
+        } else {
+            table.get(disposition.handle).ok_or(RX_ERR_BAD_HANDLE)?.duplicate_with_mask(rights)?
+        };
+        handles.push(handle);
+    }
+    Ok(handles)
+}
+
+/// Close the handles a failed write would have moved
+fn discard_handles(dispositions: &[HandleDisposition]) {
+    if let Some(table) = current_process_handle_table() {
+        for disposition in dispositions.iter().filter(|d| d.operation == HANDLE_OP_MOVE) {
+            let _ = table.remove(disposition.handle);
+        }
+    }
+}
+
+/// Install received handles in the reader's table
+///
+/// Returns their handle values. If the table fills up, the handles not yet
+/// installed are closed.
+fn install_handles(handles: Vec<Handle>) -> Result<Vec<(u32, ObjectType, Rights)>> {
+    let table = current_process_handle_table().ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    let mut installed = Vec::with_capacity(handles.len());
+    let mut handles = handles.into_iter();
+    while let Some(handle) = handles.next() {
+        let (obj_type, rights) = (handle.obj_type(), handle.rights());
+        match table.add(handle) {
+            Ok(value) => installed.push((value, obj_type, rights)),
+            Err(err) => {
+                for rest in handles {
+                    rest.close();
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(installed)
+}
+
 /// ============================================================================
 /// Syscall: Channel Write
 /// ============================================================================
@@ -299,16 +524,18 @@ pub fn sys_channel_create_impl(options: u32) -> SyscallRet {
 /// # Arguments
 ///
 /// * `handle_val` - Channel handle value
-/// * `options` - Options (must be 0)
-/// * `user_data` - User pointer to message data
-/// * `data_size` - Size of message data
-/// * `user_handles` - User pointer to handles array
+/// * `options` - 0 or `CHANNEL_WRITE_USE_IOVEC`
+/// * `user_data` - User pointer to message data, or to the iovecs
+/// * `data_size` - Size of message data, or the number of iovecs
+/// * `user_handles` - User pointer to an array of handle values to move
 /// * `handle_count` - Number of handles to transfer
 ///
 /// # Returns
 ///
 /// * On success: Number of bytes written
-/// * On error: Negative error code
+/// * On error: Negative error code; `RX_ERR_OUT_OF_RANGE` for more than
+///   `MAX_MSG_SIZE` bytes, `MAX_MSG_HANDLES` handles or `MAX_MSG_IOVECS`
+///   iovecs
 pub fn sys_channel_write_impl(
     handle_val: u32,
     options: u32,
@@ -322,75 +549,158 @@ pub fn sys_channel_write_impl(
         handle_val, options, user_data, data_size, user_handles, handle_count
     );
 
-    // Validate options (must be 0)
-    if options != 0 {
-        log_error!("sys_channel_write: invalid options {}", options);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
-    }
-
-    // Validate data size
-    if data_size > MAX_MSG_SIZE {
-        log_error!("sys_channel_write: data size too large: {}", data_size);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
-    }
-
-    // Validate handle count
     if handle_count > MAX_MSG_HANDLES {
         log_error!("sys_channel_write: handle count too large: {}", handle_count);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
+        return err_to_ret(RX_ERR_OUT_OF_RANGE);
     }
 
-    // Look up channel from handle (requires WRITE right)
-    let (channel, _handle) = match lookup_channel_from_handle(handle_val, Rights::WRITE) {
-        Ok(ch) => ch,
+    let mut values = vec![0u32; handle_count];
+    if handle_count > 0 {
+        unsafe {
+            if let Err(err) = copy_from_user(values.as_mut_ptr() as *mut u8, UserPtr::new(user_handles), handle_count * 4) {
+                log_error!("sys_channel_write: copy_from_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
+
+    // Plain writes move every handle with its rights
+    let mut dispositions: Vec<HandleDisposition> = values
+        .into_iter()
+        .map(|handle| HandleDisposition {
+            operation: HANDLE_OP_MOVE,
+            handle,
+            obj_type: 0,
+            rights: Rights::SAME_RIGHTS.into_raw(),
+            result: RX_OK,
+        })
+        .collect();
+
+    match channel_write(handle_val, options, user_data, data_size, &mut dispositions) {
+        Ok(bytes_written) => ok_to_ret(bytes_written),
         Err(err) => {
-            log_error!("sys_channel_write: failed to lookup channel: {:?}", err);
-            return err_to_ret(err);
+            log_error!("sys_channel_write: failed: {:?}", err);
+            err_to_ret(err)
         }
-    };
+    }
+}
 
-    // Read data from user space
-    let mut data = vec![0u8; data_size];
-    if data_size > 0 {
-        let user_ptr = UserPtr::new(user_data);
+/// Write to channel with handle dispositions syscall handler
+///
+/// Like `rx_channel_write`, but each handle comes with a
+/// `HandleDisposition` that moves or duplicates it, checks its object type
+/// and reduces its rights. Each disposition's `result` is written back.
+///
+/// # Arguments
+///
+/// * `handle_val` - Channel handle value
+/// * `options` - 0 or `CHANNEL_WRITE_USE_IOVEC`
+/// * `user_data` - User pointer to message data, or to the iovecs
+/// * `data_size` - Size of message data, or the number of iovecs
+/// * `user_dispositions` - User pointer to `HandleDisposition`s
+/// * `handle_count` - Number of dispositions
+///
+/// # Returns
+///
+/// * On success: Number of bytes written
+/// * On error: Negative error code; `RX_ERR_WRONG_TYPE` if a handle isn't
+///   of the expected type, `RX_ERR_INVALID_ARGS` if the rights aren't a
+///   subset of the handle's
+pub fn sys_channel_write_etc_impl(
+    handle_val: u32,
+    options: u32,
+    user_data: usize,
+    data_size: usize,
+    user_dispositions: usize,
+    handle_count: usize,
+) -> SyscallRet {
+    log_debug!(
+        "sys_channel_write_etc: handle={} options={} data={:#x} size={} dispositions={:#x} count={}",
+        handle_val, options, user_data, data_size, user_dispositions, handle_count
+    );
+
+    if handle_count > MAX_MSG_HANDLES {
+        log_error!("sys_channel_write_etc: handle count too large: {}", handle_count);
+        return err_to_ret(RX_ERR_OUT_OF_RANGE);
+    }
+
+    let len = handle_count * core::mem::size_of::<HandleDisposition>();
+    let mut dispositions = vec![HandleDisposition::default(); handle_count];
+    if handle_count > 0 {
         unsafe {
-            if let Err(err) = copy_from_user(data.as_mut_ptr(), user_ptr, data_size) {
-                log_error!("sys_channel_write: copy_from_user failed: {:?}", err);
+            if let Err(err) = copy_from_user(dispositions.as_mut_ptr() as *mut u8, UserPtr::new(user_dispositions), len) {
+                log_error!("sys_channel_write_etc: copy_from_user failed: {:?}", err);
                 return err_to_ret(err.into());
             }
         }
     }
 
-    // Read handles from user space
-    let mut handles = Vec::new();
-    if handle_count > 0 {
-        let user_handles_ptr = UserPtr::<u32>::new(user_handles);
+    let result = channel_write(handle_val, options, user_data, data_size, &mut dispositions);
 
-        // For each handle, look it up in the handle table and validate
-        // TODO: This is a simplified version - in a real implementation,
-        // we would remove the handles from the sender's handle table
-        // and transfer them to the receiver
-        for i in 0..handle_count {
-            // This is a placeholder - in a real implementation,
-            // we would read the handle value from user space
-            // and validate it has the TRANSFER right
-            let _ = i;
+    if handle_count > 0 {
+        unsafe {
+            if let Err(err) = copy_to_user(UserPtr::new(user_dispositions), dispositions.as_ptr() as *const u8, len) {
+                log_error!("sys_channel_write_etc: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
         }
     }
 
-    // Write message to channel
-    match channel.write(&data, handles) {
-        Ok(bytes_written) => {
-            log_debug!("sys_channel_write: success bytes_written={}", bytes_written);
-            ok_to_ret(bytes_written)
-        }
+    match result {
+        Ok(bytes_written) => ok_to_ret(bytes_written),
         Err(err) => {
-            log_error!("sys_channel_write: channel write failed: {:?}", err);
+            log_error!("sys_channel_write_etc: failed: {:?}", err);
             err_to_ret(err)
         }
     }
 }
 
+/// Write one message whose handles are described by `dispositions`
+fn channel_write(
+    handle_val: u32,
+    options: u32,
+    user_data: usize,
+    data_size: usize,
+    dispositions: &mut [HandleDisposition],
+) -> Result<usize> {
+    if options & !CHANNEL_WRITE_USE_IOVEC != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    if options & CHANNEL_WRITE_USE_IOVEC == 0 && data_size > MAX_MSG_SIZE {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+
+    // Look up channel from handle (requires WRITE right)
+    let (channel, channel_handle) = lookup_channel_from_handle(handle_val, Rights::WRITE)?;
+
+    // The handles are consumed from here on, even if the data is bad
+    let data = match copy_message_data(options, user_data, data_size) {
+        Ok(data) => data,
+        Err(err) => {
+            discard_handles(dispositions);
+            return Err(err);
+        }
+    };
+
+    let handles = take_handles(dispositions, &channel_handle)?;
+    let written = peer_channel(&channel)?.write(&data, handles)?;
+    return_lent_priority(&data);
+    Ok(written)
+}
+
+/// Give back a priority lent to the current thread if `data` replies to
+/// the call it was lent for
+fn return_lent_priority(data: &[u8]) {
+    let Some(current) = thread::get_current_thread() else {
+        return;
+    };
+    if let Some((_, txid)) = current.lent_priority() {
+        if data.get(..TXID_SIZE) == Some(&txid.to_le_bytes()[..]) {
+            sched::return_lent_priority(&current);
+        }
+    }
+}
+
 /// ============================================================================
 /// Syscall: Channel Read
 /// ============================================================================
@@ -400,16 +710,18 @@ pub fn sys_channel_write_impl(
 /// # Arguments
 ///
 /// * `handle_val` - Channel handle value
-/// * `options` - Read options (MAY_DISCARD)
+/// * `options` - Read options (`CHANNEL_READ_MAY_DISCARD`)
 /// * `user_data` - User pointer to data buffer
 /// * `data_capacity` - Capacity of data buffer
-/// * `user_handles` - User pointer to handles buffer
+/// * `user_handles` - User pointer to handle values buffer
 /// * `handles_capacity` - Capacity of handles buffer
 ///
 /// # Returns
 ///
 /// * On success: Number of bytes read (encoded with handle count in upper bits)
-/// * On error: Negative error code
+/// * On error: Negative error code; `RX_ERR_BUFFER_TOO_SMALL` if the next
+///   message doesn't fit, which leaves it queued unless
+///   `CHANNEL_READ_MAY_DISCARD` is set
 ///
 /// # Return Value Encoding
 ///
@@ -429,74 +741,362 @@ pub fn sys_channel_read_impl(
         handle_val, options, user_data, data_capacity, user_handles, handles_capacity
     );
 
-    // Validate options (only MAY_DISCARD allowed)
-    const MAY_DISCARD: u32 = 1;
-    if options & !MAY_DISCARD != 0 {
-        log_error!("sys_channel_read: invalid options {}", options);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
-    }
-
-    // Look up channel from handle (requires READ right)
-    let (channel, _handle) = match lookup_channel_from_handle(handle_val, Rights::READ) {
-        Ok(ch) => ch,
+    let (bytes_read, handles) = match channel_read(handle_val, options, user_data, data_capacity, handles_capacity) {
+        Ok(result) => result,
         Err(err) => {
-            log_error!("sys_channel_read: failed to lookup channel: {:?}", err);
+            log_error!("sys_channel_read: failed: {:?}", err);
             return err_to_ret(err);
         }
     };
 
-    // Allocate buffers for reading
-    let mut data_buf = vec![0u8; data_capacity];
-    let mut handle_buf = Vec::with_capacity(handles_capacity);
+    // Copy handle values to user space
+    if !handles.is_empty() {
+        let values: Vec<u32> = handles.iter().map(|&(value, _, _)| value).collect();
+        unsafe {
+            if let Err(err) = copy_to_user(UserPtr::new(user_handles), values.as_ptr() as *const u8, values.len() * 4) {
+                log_error!("sys_channel_read: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
 
-    // Read message from channel
-    let (bytes_read, handles_received) = match channel.read(&mut data_buf, &mut handle_buf) {
+    // Pack result: lower 32 bits = bytes, upper 32 bits = handles
+    let packed = (handles.len() as u64) << 32 | (bytes_read as u64);
+
+    log_debug!("sys_channel_read: success bytes_read={} handles={}",
+        bytes_read, handles.len());
+
+    ok_to_ret(packed as usize)
+}
+
+/// Read from channel with handle info syscall handler
+///
+/// Like `rx_channel_read`, but each received handle is described by a
+/// `HandleInfo` with its object type and rights.
+///
+/// # Arguments
+///
+/// * `handle_val` - Channel handle value
+/// * `options` - Read options (`CHANNEL_READ_MAY_DISCARD`)
+/// * `user_data` - User pointer to data buffer
+/// * `data_capacity` - Capacity of data buffer
+/// * `user_infos` - User pointer to `HandleInfo` buffer
+/// * `handles_capacity` - Capacity of the `HandleInfo` buffer
+///
+/// # Returns
+///
+/// * On success: bytes read and handles received, encoded as for
+///   `rx_channel_read`
+/// * On error: Negative error code
+pub fn sys_channel_read_etc_impl(
+    handle_val: u32,
+    options: u32,
+    user_data: usize,
+    data_capacity: usize,
+    user_infos: usize,
+    handles_capacity: usize,
+) -> SyscallRet {
+    log_debug!(
+        "sys_channel_read_etc: handle={} options={} data={:#x} cap={} infos={:#x} cap={}",
+        handle_val, options, user_data, data_capacity, user_infos, handles_capacity
+    );
+
+    let (bytes_read, handles) = match channel_read(handle_val, options, user_data, data_capacity, handles_capacity) {
         Ok(result) => result,
         Err(err) => {
-            // Check if buffer too small
-            if err == RX_ERR_BUFFER_TOO_SMALL {
-                // In a real implementation, we would query the message size
-                // and return it to the user
-                log_debug!("sys_channel_read: buffer too small");
-            }
-            log_error!("sys_channel_read: channel read failed: {:?}", err);
+            log_error!("sys_channel_read_etc: failed: {:?}", err);
             return err_to_ret(err);
         }
     };
 
-    // Copy data to user space
+    if !handles.is_empty() {
+        let infos: Vec<HandleInfo> = handles
+            .iter()
+            .map(|&(handle, obj_type, rights)| HandleInfo {
+                handle,
+                obj_type: obj_type.into_raw(),
+                rights: rights.into_raw(),
+                unused: 0,
+            })
+            .collect();
+        let len = infos.len() * core::mem::size_of::<HandleInfo>();
+        unsafe {
+            if let Err(err) = copy_to_user(UserPtr::new(user_infos), infos.as_ptr() as *const u8, len) {
+                log_error!("sys_channel_read_etc: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
+
+    let packed = (handles.len() as u64) << 32 | (bytes_read as u64);
+    ok_to_ret(packed as usize)
+}
+
+/// Read one message, copying its bytes out and installing its handles
+///
+/// Returns the bytes read and the installed handles.
+fn channel_read(
+    handle_val: u32,
+    options: u32,
+    user_data: usize,
+    data_capacity: usize,
+    handles_capacity: usize,
+) -> Result<(usize, Vec<(u32, ObjectType, Rights)>)> {
+    if options & !CHANNEL_READ_MAY_DISCARD != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    // Look up channel from handle (requires READ right)
+    let (channel, _handle) = lookup_channel_from_handle(handle_val, Rights::READ)?;
+
+    let may_discard = options & CHANNEL_READ_MAY_DISCARD != 0;
+    let msg = channel.take_message(data_capacity, handles_capacity, may_discard)?;
+
+    // Callers waiting on this message run on our time until we reply
+    if let Some(current) = thread::get_current_thread() {
+        channel.note_reader(current.tid());
+        if let Some(call) = msg.call {
+            sched::lend_priority(&current, call.priority, call.txid);
+        }
+    }
+
+    deliver(msg, user_data)
+}
+
+/// Copy a message's bytes to `user_data` and install its handles
+fn deliver(msg: Message, user_data: usize) -> Result<(usize, Vec<(u32, ObjectType, Rights)>)> {
+    let bytes_read = msg.data_size();
     if bytes_read > 0 {
-        let user_ptr = UserPtr::new(user_data);
+        unsafe { copy_to_user(UserPtr::new(user_data), msg.data.as_ptr(), bytes_read)? };
+    }
+
+    let installed = install_handles(msg.handles)?;
+    Ok((bytes_read, installed))
+}
+
+/// ============================================================================
+/// Syscall: Channel Call
+/// ============================================================================
+
+/// Transaction IDs handed out by `rx_channel_call_etc` have this bit set;
+/// other messages should keep it clear in their first four bytes
+pub const TXID_KERNEL_BIT: u32 = 0x8000_0000;
+
+/// Next transaction ID counter
+static NEXT_TXID: AtomicU32 = AtomicU32::new(1);
+
+/// Allocate a transaction ID for a call
+fn alloc_txid() -> u32 {
+    TXID_KERNEL_BIT | (NEXT_TXID.fetch_add(1, Ordering::Relaxed) & !TXID_KERNEL_BIT)
+}
+
+/// Arguments to `rx_channel_call_etc`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelCallEtcArgs {
+    /// User address of the request bytes; the kernel writes the
+    /// transaction ID over the first four
+    pub wr_bytes: u64,
+
+    /// User address of the request's `HandleDisposition`s
+    pub wr_handles: u64,
+
+    /// User address of the reply buffer
+    pub rd_bytes: u64,
+
+    /// User address of the reply's `HandleInfo` buffer
+    pub rd_handles: u64,
+
+    /// Request size, at least `TXID_SIZE`
+    pub wr_num_bytes: u32,
+
+    /// Number of request dispositions
+    pub wr_num_handles: u32,
+
+    /// Capacity of the reply buffer
+    pub rd_num_bytes: u32,
+
+    /// Capacity of the reply's `HandleInfo` buffer
+    pub rd_num_handles: u32,
+}
+
+/// Channel call syscall handler
+///
+/// Writes a request to the peer, as `rx_channel_write_etc` would, then
+/// waits for the reply: the first message back carrying the request's
+/// transaction ID, which the kernel allocates and writes over the first
+/// four bytes. Until the reply is written, the thread that reads the
+/// request runs at no less than the caller's priority.
+///
+/// # Arguments
+///
+/// * `handle_val` - Channel handle value (needs READ and WRITE)
+/// * `options` - Must be 0
+/// * `deadline` - Monotonic time to give up waiting, `u64::MAX` for never
+/// * `user_args` - User pointer to `ChannelCallEtcArgs`
+///
+/// # Returns
+///
+/// * On success: bytes and handles of the reply, encoded as for
+///   `rx_channel_read`
+/// * On error: Negative error code; those of `rx_channel_write_etc`,
+///   `RX_ERR_INVALID_ARGS` if the request is shorter than `TXID_SIZE`,
+///   `RX_ERR_TIMED_OUT` if the deadline passes, `RX_ERR_PEER_CLOSED` if
+///   the peer closes first, `RX_ERR_BUFFER_TOO_SMALL` if the reply
+///   doesn't fit (it is discarded)
+pub fn sys_channel_call_etc_impl(handle_val: u32, options: u32, deadline: u64, user_args: usize) -> SyscallRet {
+    log_debug!(
+        "sys_channel_call_etc: handle={} options={} deadline={} args={:#x}",
+        handle_val, options, deadline, user_args
+    );
+
+    let mut args = ChannelCallEtcArgs::default();
+    unsafe {
+        let len = core::mem::size_of::<ChannelCallEtcArgs>();
+        if let Err(err) = copy_from_user(&mut args as *mut ChannelCallEtcArgs as *mut u8, UserPtr::new(user_args), len) {
+            log_error!("sys_channel_call_etc: copy_from_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    let handle_count = args.wr_num_handles as usize;
+    if handle_count > MAX_MSG_HANDLES {
+        return err_to_ret(RX_ERR_OUT_OF_RANGE);
+    }
+
+    let len = handle_count * core::mem::size_of::<HandleDisposition>();
+    let mut dispositions = vec![HandleDisposition::default(); handle_count];
+    if handle_count > 0 {
         unsafe {
-            if let Err(err) = copy_to_user(user_ptr, data_buf.as_ptr(), bytes_read) {
-                log_error!("sys_channel_read: copy_to_user failed: {:?}", err);
+            if let Err(err) = copy_from_user(dispositions.as_mut_ptr() as *mut u8, UserPtr::new(args.wr_handles as usize), len) {
+                log_error!("sys_channel_call_etc: copy_from_user failed: {:?}", err);
                 return err_to_ret(err.into());
             }
         }
     }
 
-    // Copy handles to user space
-    if handles_received > 0 {
-        let user_handles_ptr = UserPtr::<u32>::new(user_handles);
+    let result = channel_call(handle_val, options, deadline, &args, &mut dispositions);
 
-        // TODO: Transfer handles to the receiving process
-        // In a real implementation, we would add the handles to the
-        // receiver's handle table and write the new handle values
-        for i in 0..handles_received {
-            // This is a placeholder
-            let _ = (user_handles_ptr, i);
+    if handle_count > 0 {
+        unsafe {
+            if let Err(err) = copy_to_user(UserPtr::new(args.wr_handles as usize), dispositions.as_ptr() as *const u8, len) {
+                log_error!("sys_channel_call_etc: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
         }
     }
 
-    // Pack result: lower 32 bits = bytes, upper 32 bits = handles
-    let packed = (handles_received as u64) << 32 | (bytes_read as u64);
+    let (bytes_read, handles) = match result {
+        Ok(reply) => reply,
+        Err(err) => {
+            log_debug!("sys_channel_call_etc: failed: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
 
-    log_debug!("sys_channel_read: success bytes_read={} handles={}",
-        bytes_read, handles_received);
+    if !handles.is_empty() {
+        let infos: Vec<HandleInfo> = handles
+            .iter()
+            .map(|&(handle, obj_type, rights)| HandleInfo {
+                handle,
+                obj_type: obj_type.into_raw(),
+                rights: rights.into_raw(),
+                unused: 0,
+            })
+            .collect();
+        let len = infos.len() * core::mem::size_of::<HandleInfo>();
+        unsafe {
+            if let Err(err) = copy_to_user(UserPtr::new(args.rd_handles as usize), infos.as_ptr() as *const u8, len) {
+                log_error!("sys_channel_call_etc: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
 
+    let packed = (handles.len() as u64) << 32 | (bytes_read as u64);
     ok_to_ret(packed as usize)
 }
 
+/// Write a call request and wait for its reply
+fn channel_call(
+    handle_val: u32,
+    options: u32,
+    deadline: u64,
+    args: &ChannelCallEtcArgs,
+    dispositions: &mut [HandleDisposition],
+) -> Result<(usize, Vec<(u32, ObjectType, Rights)>)> {
+    if options != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    let data_size = args.wr_num_bytes as usize;
+    if data_size > MAX_MSG_SIZE {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+    if data_size < TXID_SIZE {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    let (channel, channel_handle) = lookup_channel_from_handle(handle_val, Rights::READ | Rights::WRITE)?;
+
+    // The handles are consumed from here on, even if the data is bad
+    let mut data = match copy_message_data(0, args.wr_bytes as usize, data_size) {
+        Ok(data) => data,
+        Err(err) => {
+            discard_handles(dispositions);
+            return Err(err);
+        }
+    };
+    let txid = alloc_txid();
+    data[..TXID_SIZE].copy_from_slice(&txid.to_le_bytes());
+
+    let current = thread::get_current_thread();
+    let call = CallInfo {
+        txid,
+        caller: current.as_ref().map_or(TID_INVALID, |thread| thread.tid()),
+        priority: current.as_ref().map_or(PRIORITY_DEFAULT, |thread| thread.effective_priority()),
+    };
+
+    let handles = take_handles(dispositions, &channel_handle)?;
+    let peer = peer_channel(&channel)?;
+    peer.write_call(&data, handles, call)?;
+
+    // Run whoever serves the peer on the rest of our time slice
+    if let Some(server) = peer.last_reader() {
+        sched::yield_to(server);
+    }
+
+    // The deadline wakes us if the reply doesn't
+    let timeout = timer::Timer::new();
+    if deadline != u64::MAX {
+        timeout.set_deadline(deadline);
+        if let Some(thread) = current.as_ref() {
+            timeout.set_thread(thread.tid());
+        }
+        timeout.activate();
+        timer::insert_timer(&timeout);
+    }
+
+    let result = loop {
+        match channel.take_reply(txid, args.rd_num_bytes as usize, args.rd_num_handles as usize) {
+            Ok(reply) => break deliver(reply, args.rd_bytes as usize),
+            Err(RX_ERR_SHOULD_WAIT) => {}
+            Err(err) => break Err(err),
+        }
+        if !channel.is_peer_alive() {
+            break Err(RX_ERR_PEER_CLOSED);
+        }
+        if timer::current_time() >= deadline {
+            break Err(RX_ERR_TIMED_OUT);
+        }
+        channel.block_for_reply(txid);
+    };
+
+    if deadline != u64::MAX {
+        timer::remove_timer(&timeout);
+    }
+    result
+}
+
 /// ============================================================================
 /// Module Statistics
 /// ============================================================================
@@ -507,6 +1107,7 @@ pub fn get_stats() -> ChannelStats {
         total_channels: CHANNEL_REGISTRY.lock().count(),
         total_messages: 0, // TODO: Track total messages
         total_bytes: 0,    // TODO: Track total bytes
+        queued: message_packet::usage(),
     }
 }
 
@@ -522,6 +1123,9 @@ pub struct ChannelStats {
 
     /// Total bytes sent
     pub total_bytes: u64,
+
+    /// Messages and bytes queued in all channels
+    pub queued: PacketUsage,
 }
 
 /// ============================================================================
@@ -534,6 +1138,7 @@ pub fn init() {
     log_info!("  Max channels: {}", MAX_CHANNELS);
     log_info!("  Max message size: {}", MAX_MSG_SIZE);
     log_info!("  Max handles per message: {}", MAX_MSG_HANDLES);
+    log_info!("  Queued message pool: {} bytes", message_packet::POOL_MAX_BYTES);
 }
 
 /// ============================================================================
@@ -600,9 +1205,94 @@ mod tests {
         assert_eq!(&buf[..data.len()], data);
     }
 
+    #[test]
+    fn test_write_queues_on_peer() {
+        let (id_a, id_b) = create_pair().unwrap();
+        let channel_a = CHANNEL_REGISTRY.lock().get(id_a).unwrap();
+        let channel_b = CHANNEL_REGISTRY.lock().get(id_b).unwrap();
+
+        let peer = peer_channel(&channel_a).unwrap();
+        assert_eq!(peer.id, channel_b.id);
+        peer.write(b"ping", Vec::new()).unwrap();
+        assert_eq!(channel_a.msg_count(), 0);
+        assert_eq!(channel_b.msg_count(), 1);
+
+        write_kernel_message(id_b, b"pong").unwrap();
+        assert_eq!(channel_a.msg_count(), 1);
+        assert_eq!(channel_b.msg_count(), 1);
+
+        CHANNEL_REGISTRY.lock().remove(id_a);
+        CHANNEL_REGISTRY.lock().remove(id_b);
+    }
+
     #[test]
     fn test_message_sizes() {
         assert!(MAX_MSG_SIZE <= 64 * 1024);
         assert!(MAX_MSG_HANDLES <= 64);
     }
+
+    #[test]
+    fn test_channel_write_limits() {
+        assert_eq!(sys_channel_write_impl(1, 0x04, 0, 0, 0, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_channel_write_impl(1, 0, 0, MAX_MSG_SIZE + 1, 0, 0), err_to_ret(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(sys_channel_write_impl(1, 0, 0, 0, 0, MAX_MSG_HANDLES + 1), err_to_ret(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(sys_channel_write_etc_impl(1, 0, 0, 0, 0, MAX_MSG_HANDLES + 1), err_to_ret(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(sys_channel_read_impl(1, 0x02, 0, 0, 0, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_channel_read_etc_impl(1, 0x02, 0, 0, 0, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_alloc_txid() {
+        let a = alloc_txid();
+        let b = alloc_txid();
+        assert_ne!(a, b);
+        assert_eq!(a & TXID_KERNEL_BIT, TXID_KERNEL_BIT);
+        assert_eq!(b & TXID_KERNEL_BIT, TXID_KERNEL_BIT);
+        assert_eq!(core::mem::size_of::<ChannelCallEtcArgs>(), 48);
+    }
+
+    #[test]
+    fn test_check_disposition() {
+        let vmo = KernelObjectBase::new(ObjectType::Vmo);
+        let chan = KernelObjectBase::new(ObjectType::Channel);
+        let channel = Handle::new(&chan, Rights::READ | Rights::WRITE);
+        let handle = Handle::new(&vmo, Rights::READ | Rights::WRITE | Rights::TRANSFER);
+
+        let mut disposition = HandleDisposition {
+            operation: HANDLE_OP_MOVE,
+            handle: 0,
+            obj_type: 0,
+            rights: Rights::SAME_RIGHTS.into_raw(),
+            result: RX_OK,
+        };
+        assert_eq!(check_disposition(&disposition, &handle, &channel), Ok(handle.rights()));
+
+        // Rights may only shrink
+        disposition.rights = (Rights::READ | Rights::TRANSFER).into_raw();
+        assert_eq!(check_disposition(&disposition, &handle, &channel), Ok(Rights::READ | Rights::TRANSFER));
+        disposition.rights = Rights::MAP.into_raw();
+        assert_eq!(check_disposition(&disposition, &handle, &channel), Err(RX_ERR_INVALID_ARGS));
+        disposition.rights = Rights::SAME_RIGHTS.into_raw();
+
+        disposition.obj_type = ObjectType::Event.into_raw();
+        assert_eq!(check_disposition(&disposition, &handle, &channel), Err(RX_ERR_WRONG_TYPE));
+        disposition.obj_type = ObjectType::Vmo.into_raw();
+        assert!(check_disposition(&disposition, &handle, &channel).is_ok());
+
+        // Duplicating needs DUPLICATE, anything needs TRANSFER
+        disposition.operation = HANDLE_OP_DUPLICATE;
+        assert_eq!(check_disposition(&disposition, &handle, &channel), Err(RX_ERR_ACCESS_DENIED));
+        let untransferable = Handle::new(&vmo, Rights::READ);
+        disposition.operation = HANDLE_OP_MOVE;
+        assert_eq!(check_disposition(&disposition, &untransferable, &channel), Err(RX_ERR_ACCESS_DENIED));
+
+        disposition.operation = 2;
+        assert_eq!(check_disposition(&disposition, &handle, &channel), Err(RX_ERR_INVALID_ARGS));
+
+        // Not the channel itself
+        disposition.operation = HANDLE_OP_MOVE;
+        disposition.obj_type = 0;
+        let own = Handle::new(&chan, Rights::READ | Rights::TRANSFER);
+        assert_eq!(check_disposition(&disposition, &own, &channel), Err(RX_ERR_NOT_SUPPORTED));
+    }
 }
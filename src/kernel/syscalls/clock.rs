@@ -0,0 +1,312 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Clock System Calls
+//!
+//! This module implements the clock system calls.
+//!
+//! # Syscalls Implemented
+//!
+//! - `rx_clock_get` - Read the monotonic clock or the caller's UTC clock
+//! - `rx_clock_create` - Create a clock object
+//! - `rx_clock_read` - Read a clock object
+//! - `rx_clock_get_details` - Get a clock's transform and update history
+//! - `rx_clock_update` - Set a clock's value, rate, slew or error bound
+//! - `rx_utc_reference_get` - Get the caller's UTC clock
+//! - `rx_utc_reference_swap` - Replace the caller's UTC clock
+//!
+//! # Design
+//!
+//! UTC is not kept by the kernel. Each process has a UTC reference, a
+//! clock object it reads as UTC, inherited from its creator; unless
+//! someone swapped it, that is the system UTC clock created at boot. A
+//! time daemon holding the system clock's handle disciplines it with
+//! `rx_clock_update` (see [`crate::kernel::object::clock`]).
+//!
+//! Clock handle values are registry IDs, like counters, until clocks are
+//! installed in the process's handle table.
+
+use crate::kernel::object::clock::{self as clock_object, Clock, ClockId, ClockUpdate};
+use crate::kernel::process::{self, Process};
+use crate::kernel::syscalls::system::{validate_resource, ResourceKind};
+use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::thread;
+use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use alloc::sync::Arc;
+
+// Import logging macros
+use crate::{log_debug, log_error};
+
+/// Clock IDs accepted by `rx_clock_get`
+pub mod clock_id {
+    /// Time since boot
+    pub const MONOTONIC: u32 = 0;
+
+    /// The caller's UTC clock
+    pub const UTC: u32 = 1;
+}
+
+/// ============================================================================
+/// Helpers
+/// ============================================================================
+
+/// Copy a plain value out to user memory
+fn write_user<T: Copy>(ptr: usize, value: &T) -> Result {
+    let user_ptr = UserPtr::<u8>::new(ptr);
+    unsafe {
+        copy_to_user(user_ptr, value as *const T as *const u8, core::mem::size_of::<T>())
+            .map_err(|err| err.into())
+    }
+}
+
+/// The process the current thread belongs to
+fn current_process() -> Option<&'static Process> {
+    thread::get_current_thread()
+        .and_then(|thread| thread.pid())
+        .and_then(process::lookup)
+}
+
+/// Look up a clock by handle value
+fn lookup_clock(handle: u32) -> Result<Arc<Clock>> {
+    clock_object::get(handle as ClockId).map_err(|_| RX_ERR_BAD_HANDLE)
+}
+
+/// The clock the caller reads as UTC
+pub fn current_utc_reference() -> Option<ClockId> {
+    current_process()
+        .and_then(|process| process.utc_clock())
+        .or_else(clock_object::system_utc)
+}
+
+/// ============================================================================
+/// Syscall: Clock Get
+/// ============================================================================
+
+/// Read a kernel clock syscall handler
+///
+/// # Arguments
+///
+/// * `clock_id` - `clock_id::MONOTONIC` or `clock_id::UTC`
+///
+/// # Returns
+///
+/// * On success: The time in nanoseconds
+/// * On error: Negative error code
+///   - `RX_ERR_INVALID_ARGS` for an unknown clock
+///   - `RX_ERR_BAD_STATE` if the caller has no UTC clock
+pub fn sys_clock_get_impl(clock_id: u32) -> SyscallRet {
+    match clock_id {
+        clock_id::MONOTONIC => ok_to_ret(crate::kernel::timer::current_time() as usize),
+        clock_id::UTC => match current_utc_reference().map(clock_object::get) {
+            Some(Ok(clock)) => ok_to_ret(clock.read() as usize),
+            _ => err_to_ret(RX_ERR_BAD_STATE),
+        },
+        _ => err_to_ret(RX_ERR_INVALID_ARGS),
+    }
+}
+
+/// ============================================================================
+/// Syscall: Clock Create
+/// ============================================================================
+
+/// Create a clock syscall handler
+///
+/// # Arguments
+///
+/// * `options` - `clock_opt` bits
+/// * `backstop` - Earliest time the clock reads
+/// * `clock_out` - User pointer to store the clock handle (u32)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_clock_create_impl(options: u64, backstop: i64, clock_out: usize) -> SyscallRet {
+    log_debug!("sys_clock_create: options={:#x} backstop={}", options, backstop);
+
+    let handle = match clock_object::create(options, backstop) {
+        Ok(id) => id as u32,
+        Err(err) => {
+            log_error!("sys_clock_create: failed: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    if let Err(err) = write_user(clock_out, &handle) {
+        log_error!("sys_clock_create: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: Clock Read
+/// ============================================================================
+
+/// Read a clock syscall handler
+///
+/// # Arguments
+///
+/// * `handle` - Clock handle
+/// * `now_out` - User pointer to store the time (i64)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_clock_read_impl(handle: u32, now_out: usize) -> SyscallRet {
+    let clock = match lookup_clock(handle) {
+        Ok(clock) => clock,
+        Err(err) => return err_to_ret(err),
+    };
+
+    match write_user(now_out, &clock.read()) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: Clock Get Details
+/// ============================================================================
+
+/// Get clock details syscall handler
+///
+/// # Arguments
+///
+/// * `handle` - Clock handle
+/// * `details_out` - User pointer to a `ClockDetails`
+/// * `details_size` - Size of the buffer, which must fit a `ClockDetails`
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_clock_get_details_impl(handle: u32, details_out: usize, details_size: usize) -> SyscallRet {
+    let clock = match lookup_clock(handle) {
+        Ok(clock) => clock,
+        Err(err) => return err_to_ret(err),
+    };
+
+    if details_size < core::mem::size_of::<clock_object::ClockDetails>() {
+        return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+    }
+
+    match write_user(details_out, &clock.details()) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: Clock Update
+/// ============================================================================
+
+/// Update a clock syscall handler
+///
+/// # Arguments
+///
+/// * `handle` - Clock handle
+/// * `args` - User pointer to a `ClockUpdate`
+/// * `args_size` - Size of the `ClockUpdate`
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code (see [`Clock::update`])
+pub fn sys_clock_update_impl(handle: u32, args: usize, args_size: usize) -> SyscallRet {
+    let clock = match lookup_clock(handle) {
+        Ok(clock) => clock,
+        Err(err) => return err_to_ret(err),
+    };
+
+    if args_size != core::mem::size_of::<ClockUpdate>() {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    let mut update = ClockUpdate::default();
+    unsafe {
+        if let Err(err) = copy_from_user(
+            &mut update as *mut ClockUpdate as *mut u8,
+            UserPtr::<u8>::new(args),
+            args_size,
+        ) {
+            log_error!("sys_clock_update: copy_from_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    log_debug!("sys_clock_update: handle={:#x} flags={:#x}", handle, update.flags);
+    match clock.update(&update) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: UTC Reference
+/// ============================================================================
+
+/// Get the caller's UTC clock syscall handler
+///
+/// # Returns
+///
+/// * On success: Handle of the clock the caller reads as UTC
+/// * On error: `RX_ERR_NOT_FOUND` if there is none
+pub fn sys_utc_reference_get_impl() -> SyscallRet {
+    match current_utc_reference() {
+        Some(id) => ok_to_ret(id as usize),
+        None => err_to_ret(RX_ERR_NOT_FOUND),
+    }
+}
+
+/// Replace the caller's UTC clock syscall handler
+///
+/// New processes inherit the clock, so this is how a launcher hands a
+/// sandbox a clock of its own.
+///
+/// # Arguments
+///
+/// * `resource` - Root resource
+/// * `handle` - New UTC clock, or 0 for the system UTC clock
+/// * `prev_out` - User pointer to store the previous clock (u32), or 0
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_utc_reference_swap_impl(resource: u32, handle: u32, prev_out: usize) -> SyscallRet {
+    if let Err(err) = validate_resource(resource, ResourceKind::Root) {
+        log_error!("sys_utc_reference_swap: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let clock = match handle {
+        0 => None,
+        handle => match lookup_clock(handle) {
+            Ok(clock) => Some(clock.id),
+            Err(err) => return err_to_ret(err),
+        },
+    };
+
+    let process = match current_process() {
+        Some(process) => process,
+        None => return err_to_ret(RX_ERR_BAD_STATE),
+    };
+
+    let previous = process.swap_utc_clock(clock).or_else(clock_object::system_utc);
+    if prev_out != 0 {
+        let previous = previous.unwrap_or(0) as u32;
+        if let Err(err) = write_user(prev_out, &previous) {
+            return err_to_ret(err);
+        }
+    }
+
+    ok_to_ret(0)
+}
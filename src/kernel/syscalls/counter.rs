@@ -0,0 +1,338 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Counter System Calls
+//!
+//! This module implements the counter system calls.
+//!
+//! # Syscalls Implemented
+//!
+//! - `rx_counter_create` - Create a counter
+//! - `rx_counter_add` - Add to a counter's value
+//! - `rx_counter_read` - Read a counter's value
+//! - `rx_counter_write` - Overwrite a counter's value
+//!
+//! # Design
+//!
+//! A counter is a shared signed value with `COUNTER_POSITIVE` and
+//! `COUNTER_NON_POSITIVE` signals (see [`crate::kernel::object::counter`]).
+//! Waiters are woken whenever an update moves the value across zero, so a
+//! producer can add one per item while a consumer waits for
+//! `COUNTER_POSITIVE` and subtracts what it took.
+//!
+//! Counter handle values are registry IDs, like profiles, until counters
+//! are installed in the process's handle table.
+
+
+use crate::kernel::object::counter::{Counter, CounterId};
+use crate::kernel::sync::Mutex;
+use crate::kernel::usercopy::{copy_to_user, UserPtr};
+use crate::kernel::syscalls::object_wait;
+use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+
+// Import logging macros
+use crate::{log_debug, log_error, log_info};
+
+/// ============================================================================
+/// Counter Registry
+/// ============================================================================
+
+/// Maximum number of counters in the system
+const MAX_COUNTERS: usize = 4096;
+
+/// Global counter registry
+///
+/// Maps counter IDs to counter objects. This is used to resolve handles to
+/// counters.
+static COUNTER_REGISTRY: Mutex<BTreeMap<CounterId, Arc<Counter>>> = Mutex::new(BTreeMap::new());
+
+/// Look up a counter by handle value
+fn lookup_counter(handle: u32) -> Option<Arc<Counter>> {
+    COUNTER_REGISTRY.lock().get(&(handle as CounterId)).cloned()
+}
+
+/// Wake waiters on a counter whose signals just changed
+fn notify(handle: u32, counter: &Counter) {
+    let signals = counter.signals();
+    let woken = object_wait::wake_waiters(handle, signals);
+    log_debug!("counter {}: signals={:#x}, woke {} waiters", counter.id(), signals, woken);
+}
+
+/// ============================================================================
+/// Syscall: Counter Create
+/// ============================================================================
+
+/// Create a counter syscall handler
+///
+/// # Arguments
+///
+/// * `options` - Creation options (must be 0)
+///
+/// # Returns
+///
+/// * On success: Handle value for the new counter, starting at 0
+/// * On error: Negative error code
+pub fn sys_counter_create_impl(options: u32) -> SyscallRet {
+    log_debug!("sys_counter_create: options={:#x}", options);
+
+    if options != 0 {
+        log_error!("sys_counter_create: invalid options");
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    let counter = Arc::new(Counter::new());
+    let handle_value = counter.id() as u32;
+
+    {
+        let mut registry = COUNTER_REGISTRY.lock();
+        if registry.len() >= MAX_COUNTERS {
+            log_error!("sys_counter_create: too many counters");
+            return err_to_ret(RX_ERR_NO_RESOURCES);
+        }
+        registry.insert(counter.id(), counter);
+    }
+
+    log_debug!("sys_counter_create: success handle={:#x}", handle_value);
+    ok_to_ret(handle_value as usize)
+}
+
+/// ============================================================================
+/// Syscall: Counter Add
+/// ============================================================================
+
+/// Add to a counter syscall handler
+///
+/// # Arguments
+///
+/// * `handle` - Counter handle
+/// * `amount` - Signed amount to add
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+///   - `RX_ERR_BAD_HANDLE` if the handle is not a counter
+///   - `RX_ERR_OUT_OF_RANGE` if the value would overflow; it is unchanged
+pub fn sys_counter_add_impl(handle: u32, amount: i64) -> SyscallRet {
+    log_debug!("sys_counter_add: handle={:#x} amount={}", handle, amount);
+
+    let counter = match lookup_counter(handle) {
+        Some(counter) => counter,
+        None => return err_to_ret(RX_ERR_BAD_HANDLE),
+    };
+
+    match counter.add(amount) {
+        Ok(changed) => {
+            if changed {
+                notify(handle, &counter);
+            }
+            ok_to_ret(0)
+        }
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: Counter Read
+/// ============================================================================
+
+/// Read a counter syscall handler
+///
+/// # Arguments
+///
+/// * `handle` - Counter handle
+/// * `value_out` - User pointer to store the value (i64)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_counter_read_impl(handle: u32, value_out: usize) -> SyscallRet {
+    log_debug!("sys_counter_read: handle={:#x}", handle);
+
+    let counter = match lookup_counter(handle) {
+        Some(counter) => counter,
+        None => return err_to_ret(RX_ERR_BAD_HANDLE),
+    };
+
+    if value_out == 0 {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    let value = counter.read();
+    let out_ptr = UserPtr::<u8>::new(value_out);
+    unsafe {
+        if let Err(err) = copy_to_user(out_ptr, &value as *const i64 as *const u8, 8) {
+            log_error!("sys_counter_read: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: Counter Write
+/// ============================================================================
+
+/// Overwrite a counter syscall handler
+///
+/// # Arguments
+///
+/// * `handle` - Counter handle
+/// * `value` - New value
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_counter_write_impl(handle: u32, value: i64) -> SyscallRet {
+    log_debug!("sys_counter_write: handle={:#x} value={}", handle, value);
+
+    let counter = match lookup_counter(handle) {
+        Some(counter) => counter,
+        None => return err_to_ret(RX_ERR_BAD_HANDLE),
+    };
+
+    if counter.write(value) {
+        notify(handle, &counter);
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Wait and Close
+/// ============================================================================
+
+/// Get the signals asserted on a counter
+///
+/// Returns `None` if `handle` is not a counter.
+pub fn counter_signals(handle: u32) -> Option<u64> {
+    lookup_counter(handle).map(|counter| counter.signals())
+}
+
+/// Get the koid of a counter
+///
+/// Returns `None` if `handle` is not a counter.
+pub fn counter_koid(handle: u32) -> Option<u64> {
+    lookup_counter(handle).map(|counter| counter.koid())
+}
+
+/// Drop a handle to a counter, destroying it with the last one
+///
+/// Returns false if `handle` is not a counter.
+pub fn close_counter_handle(handle: u32) -> bool {
+    let counter = match lookup_counter(handle) {
+        Some(counter) => counter,
+        None => return false,
+    };
+
+    if counter.ref_dec() {
+        COUNTER_REGISTRY.lock().remove(&counter.id());
+        log_debug!("close_counter_handle: destroyed counter {}", counter.id());
+    }
+
+    true
+}
+
+/// ============================================================================
+/// Module Statistics
+/// ============================================================================
+
+/// Get counter subsystem statistics
+pub fn get_stats() -> CounterStats {
+    CounterStats {
+        total_counters: COUNTER_REGISTRY.lock().len(),
+    }
+}
+
+/// Counter subsystem statistics
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CounterStats {
+    /// Total number of counters
+    pub total_counters: usize,
+}
+
+/// ============================================================================
+/// Module Initialization
+/// ============================================================================
+
+/// Initialize the counter syscall subsystem
+pub fn init() {
+    log_info!("Counter syscall subsystem initialized");
+    log_info!("  Max counters: {}", MAX_COUNTERS);
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::object::counter::{COUNTER_NON_POSITIVE, COUNTER_POSITIVE};
+
+    fn create() -> u32 {
+        sys_counter_create_impl(0) as u32
+    }
+
+    #[test]
+    fn test_counter_create_options() {
+        assert_eq!(sys_counter_create_impl(1), err_to_ret(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_counter_add_and_write() {
+        let handle = create();
+        assert_eq!(counter_signals(handle), Some(COUNTER_NON_POSITIVE));
+
+        assert_eq!(sys_counter_add_impl(handle, 3), 0);
+        assert_eq!(counter_signals(handle), Some(COUNTER_POSITIVE));
+
+        assert_eq!(sys_counter_write_impl(handle, -2), 0);
+        assert_eq!(counter_signals(handle), Some(COUNTER_NON_POSITIVE));
+        assert_eq!(lookup_counter(handle).unwrap().read(), -2);
+    }
+
+    #[test]
+    fn test_counter_add_overflow() {
+        let handle = create();
+        assert_eq!(sys_counter_write_impl(handle, i64::MAX), 0);
+        assert_eq!(sys_counter_add_impl(handle, 1), err_to_ret(RX_ERR_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn test_counter_bad_handle() {
+        assert_eq!(sys_counter_add_impl(0xdead_beef, 1), err_to_ret(RX_ERR_BAD_HANDLE));
+        assert_eq!(sys_counter_write_impl(0xdead_beef, 1), err_to_ret(RX_ERR_BAD_HANDLE));
+        assert_eq!(sys_counter_read_impl(0xdead_beef, 0), err_to_ret(RX_ERR_BAD_HANDLE));
+        assert_eq!(counter_signals(0xdead_beef), None);
+    }
+
+    #[test]
+    fn test_counter_read_null() {
+        let handle = create();
+        assert_eq!(sys_counter_read_impl(handle, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_counter_close() {
+        let handle = create();
+        lookup_counter(handle).unwrap().ref_inc();
+
+        assert!(close_counter_handle(handle));
+        assert!(counter_signals(handle).is_some());
+        assert!(close_counter_handle(handle));
+        assert!(counter_signals(handle).is_none());
+        assert!(!close_counter_handle(handle));
+    }
+}
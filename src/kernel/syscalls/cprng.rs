@@ -0,0 +1,111 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! CPRNG System Calls
+//!
+//! This module exposes the kernel CPRNG to userspace.
+//!
+//! # Syscalls Implemented
+//!
+//! - `rx_cprng_draw` - Fill a buffer with random bytes
+//! - `rx_cprng_add_entropy` - Mix caller-supplied data into the CPRNG
+//!
+//! # Design
+//!
+//! - No handle or rights are needed for either call
+//! - Requests are limited to 256 bytes; larger draws are made in a loop
+//! - Added entropy is mixed but never credited, so an unprivileged
+//!   caller cannot make the kernel think it is better seeded than it is
+
+
+use crate::kernel::lib::crypto::{self, MAX_DRAW_LEN, MAX_ENTROPY_LEN};
+use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
+use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::rustux::types::err::*;
+
+// Import logging macros
+use crate::{log_debug, log_error};
+
+/// ============================================================================
+/// Syscall: CPRNG Draw
+/// ============================================================================
+
+/// Fill a user buffer with random bytes
+///
+/// # Arguments
+///
+/// * `buffer` - User buffer
+/// * `len` - Bytes to write (at most `MAX_DRAW_LEN`)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_cprng_draw_impl(buffer: usize, len: usize) -> SyscallRet {
+    log_debug!("sys_cprng_draw: len={}", len);
+
+    if len > MAX_DRAW_LEN {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+    if len == 0 {
+        return ok_to_ret(0);
+    }
+
+    let mut bytes = [0u8; MAX_DRAW_LEN];
+    crypto::draw(&mut bytes[..len]);
+
+    let user_ptr = UserPtr::<u8>::new(buffer);
+    let result = unsafe { copy_to_user(user_ptr, bytes.as_ptr(), len) };
+
+    // Do not leave the bytes on the kernel stack
+    bytes.fill(0);
+
+    match result {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => {
+            log_error!("sys_cprng_draw: copy_to_user failed: {:?}", err);
+            err_to_ret(err.into())
+        }
+    }
+}
+
+/// ============================================================================
+/// Syscall: CPRNG Add Entropy
+/// ============================================================================
+
+/// Mix a user buffer into the CPRNG
+///
+/// # Arguments
+///
+/// * `buffer` - User buffer
+/// * `len` - Bytes to read (at most `MAX_ENTROPY_LEN`)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_cprng_add_entropy_impl(buffer: usize, len: usize) -> SyscallRet {
+    log_debug!("sys_cprng_add_entropy: len={}", len);
+
+    if len > MAX_ENTROPY_LEN {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+    if len == 0 {
+        return ok_to_ret(0);
+    }
+
+    let mut bytes = [0u8; MAX_ENTROPY_LEN];
+    let user_ptr = UserPtr::<u8>::new(buffer);
+    if let Err(err) = unsafe { copy_from_user(bytes.as_mut_ptr(), user_ptr, len) } {
+        log_error!("sys_cprng_add_entropy: copy_from_user failed: {:?}", err);
+        return err_to_ret(err.into());
+    }
+
+    crypto::add_entropy(&bytes[..len]);
+    bytes.fill(0);
+
+    ok_to_ret(0)
+}
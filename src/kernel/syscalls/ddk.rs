@@ -29,10 +29,30 @@
 //! - `rx_interrupt_destroy` - Destroy interrupt
 //! - `rx_interrupt_trigger` - Trigger virtual interrupt
 //! - `rx_smc_call` - SMC call (ARM)
-
-
+//!
+//! # DMA
+//!
+//! Device addresses come from pinning VMOs through a BTI (see
+//! [`object::bti`](crate::kernel::object::bti)). A BTI created against a
+//! hardware IOMMU object gets its own IO address space, and a device
+//! without one can't reach memory at all. Passing handle 0 to
+//! `rx_bti_create` asks for a dummy IOMMU, which hands out physical
+//! addresses; that is only allowed until a hardware IOMMU is enabled.
+
+use crate::kernel::dev::fbcon;
+use crate::kernel::dev::iommu::{DummyIommu, Iommu};
+use crate::kernel::handoff;
+use crate::kernel::object::bti::{self, Bti, BtiId};
+use crate::kernel::object::interrupt::{self, InterruptFlags};
+use crate::kernel::object::iommu::{self as iommu_object, IommuKind};
+use crate::kernel::object::pmt::{self, PmtId};
+use crate::kernel::object::vmo::Vmo;
+use crate::kernel::object::Rights;
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::resource;
+use crate::kernel::syscalls::system::{validate_ranged_resource, validate_resource, ResourceKind};
+use crate::kernel::syscalls::vmo::{lookup_vmo_from_handle, register_vmo};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
@@ -48,8 +68,32 @@ use crate::{log_debug, log_error, log_info, log_warn};
 /// Page size shift (for 4KB pages)
 const PAGE_SIZE_SHIFT: u32 = 12;
 
-/// Maximum IOMMU descriptor length
-const IOMMU_MAX_DESC_LEN: usize = 256;
+/// ============================================================================
+/// Statistics Counters
+/// ============================================================================
+
+static TOTAL_VMO_CONTIGUOUS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_VMO_PHYSICAL: AtomicU64 = AtomicU64::new(0);
+static TOTAL_INTERRUPTS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_BTI_OPS: AtomicU64 = AtomicU64::new(0);
+
+/// ============================================================================
+/// Helpers
+/// ============================================================================
+
+/// Copy a plain value out to user memory
+fn write_user<T: Copy>(ptr: usize, value: &T) -> Result {
+    let user_ptr = UserPtr::<u8>::new(ptr);
+    unsafe {
+        copy_to_user(user_ptr, value as *const T as *const u8, core::mem::size_of::<T>())
+            .map_err(|err| err.into())
+    }
+}
+
+/// Look up a BTI by handle value
+fn lookup_bti(handle: u32) -> Result<Arc<Bti>> {
+    bti::get(handle as BtiId).map_err(|_| RX_ERR_BAD_HANDLE)
+}
 
 /// ============================================================================
 /// BTI (Bus Transaction Initiator) Options
@@ -92,6 +136,9 @@ pub mod interrupt_flags {
 
 /// IOMMU types
 pub mod iommu_type {
+    /// No translation: device addresses are physical addresses
+    pub const DUMMY: u32 = 0;
+
     /// Intel IOMMU (VT-d)
     pub const INTEL: u32 = 1;
 
@@ -102,6 +149,16 @@ pub mod iommu_type {
     pub const BROADCOM: u32 = 3;
 }
 
+/// ============================================================================
+/// Framebuffer Options
+/// ============================================================================
+
+/// `rx_framebuffer_get_info` options
+pub mod framebuffer_options {
+    /// Stop the kernel's framebuffer console
+    pub const TAKE_OVER: u32 = 0x01;
+}
+
 /// ============================================================================
 /// VMO Create Contiguous
 /// ============================================================================
@@ -146,18 +203,23 @@ pub fn sys_vmo_create_contiguous_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Validate BTI handle
-    // TODO: Create contiguous VMO
-    // TODO: Return VMO handle
+    if let Err(err) = lookup_bti(bti_handle) {
+        return err_to_ret(err);
+    }
 
-    // For now, return a stub handle
-    let vmo_handle = 1000u32;
-    let user_ptr = UserPtr::<u8>::new(vmo_out);
-    unsafe {
-        if let Err(err) = copy_to_user(user_ptr, &vmo_handle as *const u32 as *const u8, 4) {
-            log_error!("sys_vmo_create_contiguous: copy_to_user failed: {:?}", err);
-            return err_to_ret(err.into());
+    let size = (size + 0xFFF) & !0xFFF;
+    let vmo_handle = match Vmo::create_contiguous(size, alignment_log2 as u8).and_then(register_vmo) {
+        Ok(handle) => handle,
+        Err(err) => {
+            log_error!("sys_vmo_create_contiguous: failed to create VMO: {:?}", err);
+            return err_to_ret(err);
         }
+    };
+    TOTAL_VMO_CONTIGUOUS.fetch_add(1, Ordering::Relaxed);
+
+    if let Err(err) = write_user(vmo_out, &vmo_handle) {
+        log_error!("sys_vmo_create_contiguous: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
     }
 
     log_debug!("sys_vmo_create_contiguous: success handle={:#x}", vmo_handle);
@@ -172,7 +234,7 @@ pub fn sys_vmo_create_contiguous_impl(
 ///
 /// # Arguments
 ///
-/// * `rsrc_handle` - Resource handle
+/// * `rsrc_handle` - Root resource, or an MMIO resource covering the range
 /// * `paddr` - Physical address
 /// * `size` - Size of region
 /// * `vmo_out` - User pointer to store VMO handle
@@ -194,18 +256,23 @@ pub fn sys_vmo_create_physical_impl(
         size
     );
 
-    // TODO: Validate resource handle for MMIO region
-    // TODO: Create physical VMO
-    // TODO: Return VMO handle
+    if let Err(err) = validate_ranged_resource(rsrc_handle, ResourceKind::Mmio, paddr, size as u64) {
+        log_error!("sys_vmo_create_physical: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
 
-    // For now, return a stub handle
-    let vmo_handle = 1001u32;
-    let user_ptr = UserPtr::<u8>::new(vmo_out);
-    unsafe {
-        if let Err(err) = copy_to_user(user_ptr, &vmo_handle as *const u32 as *const u8, 4) {
-            log_error!("sys_vmo_create_physical: copy_to_user failed: {:?}", err);
-            return err_to_ret(err.into());
+    let vmo_handle = match Vmo::create_physical(paddr, size).and_then(register_vmo) {
+        Ok(handle) => handle,
+        Err(err) => {
+            log_error!("sys_vmo_create_physical: failed to create VMO: {:?}", err);
+            return err_to_ret(err);
         }
+    };
+    TOTAL_VMO_PHYSICAL.fetch_add(1, Ordering::Relaxed);
+
+    if let Err(err) = write_user(vmo_out, &vmo_handle) {
+        log_error!("sys_vmo_create_physical: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
     }
 
     log_debug!("sys_vmo_create_physical: success handle={:#x}", vmo_handle);
@@ -216,37 +283,56 @@ pub fn sys_vmo_create_physical_impl(
 /// Framebuffer Get Info
 /// ============================================================================
 
-/// Get framebuffer information (x86 only)
+/// Get the boot framebuffer
+///
+/// Describes the linear framebuffer the loader handed over (physical base,
+/// size, geometry and pixel format, as in the handoff). With
+/// `framebuffer_options::TAKE_OVER` the kernel's framebuffer console stops
+/// drawing, so a userspace console can map the framebuffer and own it;
+/// kernel log output then only goes to the serial console.
 ///
 /// # Arguments
 ///
 /// * `handle` - Root resource handle
-/// * `format_out` - User pointer to store format
-/// * `width_out` - User pointer to store width
-/// * `height_out` - User pointer to store height
-/// * `stride_out` - User pointer to store stride
+/// * `info_out` - User pointer to store a `FramebufferInfo`
+/// * `options` - `framebuffer_options` flags
 ///
 /// # Returns
 ///
 /// * On success: 0
-/// * On error: Negative error code
-pub fn sys_framebuffer_get_info_impl(
-    handle: u32,
-    format_out: usize,
-    width_out: usize,
-    height_out: usize,
-    stride_out: usize,
-) -> SyscallRet {
+/// * On error: Negative error code, NOT_FOUND if the loader provided no
+///   framebuffer
+pub fn sys_framebuffer_get_info_impl(handle: u32, info_out: usize, options: u32) -> SyscallRet {
     log_debug!(
-        "sys_framebuffer_get_info: handle={:#x}",
-        handle
+        "sys_framebuffer_get_info: handle={:#x} options={:#x}",
+        handle,
+        options
     );
 
-    // TODO: Validate resource handle
-    // TODO: Implement for x86 (bootloader framebuffer)
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_framebuffer_get_info: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+    if options & !framebuffer_options::TAKE_OVER != 0 {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
 
-    log_warn!("sys_framebuffer_get_info: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    let info = match handoff::get().and_then(|h| h.framebuffer()) {
+        Some(info) => *info,
+        None => return err_to_ret(RX_ERR_NOT_FOUND),
+    };
+
+    if let Err(err) = write_user(info_out, &info) {
+        log_error!("sys_framebuffer_get_info: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    if options & framebuffer_options::TAKE_OVER != 0 && fbcon::is_enabled() {
+        log_info!("sys_framebuffer_get_info: framebuffer console handed to userspace");
+        fbcon::disable();
+    }
+
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -304,12 +390,16 @@ pub fn sys_framebuffer_set_range_impl(
 
 /// Create IOMMU
 ///
+/// The kernel finds the hardware itself from the DMAR or IORT table, so
+/// the descriptor must be empty. The first Intel or SMMU object enables
+/// translation.
+///
 /// # Arguments
 ///
 /// * `resource` - Root resource handle
 /// * `type` - IOMMU type
-/// * `desc` - User pointer to IOMMU descriptor
-/// * `desc_size` - Descriptor size
+/// * `desc` - User pointer to IOMMU descriptor (unused)
+/// * `desc_size` - Descriptor size (must be 0)
 /// * `iommu_out` - User pointer to store IOMMU handle
 ///
 /// # Returns
@@ -319,7 +409,7 @@ pub fn sys_framebuffer_set_range_impl(
 pub fn sys_iommu_create_impl(
     resource: u32,
     type_: u32,
-    desc: usize,
+    _desc: usize,
     desc_size: usize,
     iommu_out: usize,
 ) -> SyscallRet {
@@ -330,24 +420,32 @@ pub fn sys_iommu_create_impl(
         desc_size
     );
 
-    if desc_size > IOMMU_MAX_DESC_LEN {
-        log_error!("sys_iommu_create: desc too large");
+    if let Err(err) = validate_resource(resource, ResourceKind::Root) {
+        log_error!("sys_iommu_create: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    if desc_size != 0 {
+        log_error!("sys_iommu_create: descriptors are not supported");
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Validate resource handle
-    // TODO: Copy descriptor from user
-    // TODO: Create IOMMU
-    // TODO: Return IOMMU handle
+    let kind = match IommuKind::try_from_raw(type_) {
+        Some(kind) => kind,
+        None => return err_to_ret(RX_ERR_NOT_SUPPORTED),
+    };
 
-    // For now, return a stub handle
-    let iommu_handle = 2000u32;
-    let user_ptr = UserPtr::<u8>::new(iommu_out);
-    unsafe {
-        if let Err(err) = copy_to_user(user_ptr, &iommu_handle as *const u32 as *const u8, 4) {
-            log_error!("sys_iommu_create: copy_to_user failed: {:?}", err);
-            return err_to_ret(err.into());
+    let iommu_handle = match iommu_object::create(kind) {
+        Ok(id) => id as u32,
+        Err(err) => {
+            log_error!("sys_iommu_create: failed to create IOMMU: {:?}", err);
+            return err_to_ret(err);
         }
+    };
+
+    if let Err(err) = write_user(iommu_out, &iommu_handle) {
+        log_error!("sys_iommu_create: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
     }
 
     log_debug!("sys_iommu_create: success handle={:#x}", iommu_handle);
@@ -360,9 +458,14 @@ pub fn sys_iommu_create_impl(
 
 /// Request I/O port access (x86 only)
 ///
+/// The ports are added to the calling process's I/O permission bitmap.
+/// The grant takes effect on this CPU at once and on others the next time
+/// they switch to one of the process's threads; it lasts as long as the
+/// process.
+///
 /// # Arguments
 ///
-/// * `rsrc_handle` - Resource handle
+/// * `rsrc_handle` - Root resource, or an I/O port resource covering the ports
 /// * `io_addr` - I/O port address
 /// * `len` - Number of ports
 ///
@@ -382,10 +485,34 @@ pub fn sys_ioports_request_impl(
         len
     );
 
-    // TODO: Validate resource handle for I/O port range
-    // TODO: Set I/O bitmap for current process
+    if len == 0 || io_addr as u64 + len as u64 > resource::IOPORT_COUNT {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    if let Err(err) = validate_ranged_resource(rsrc_handle, ResourceKind::IoPort, io_addr as u64, len as u64) {
+        log_error!("sys_ioports_request: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        use crate::kernel::arch::amd64::ioport::{x86_load_io_bitmap, IoBitmap};
+
+        let process = crate::kernel::thread::get_current_thread()
+            .and_then(|thread| thread.pid())
+            .and_then(crate::kernel::process::lookup);
+        let Some(process) = process else {
+            return err_to_ret(RX_ERR_BAD_STATE);
+        };
+
+        let mut bitmap = process.io_bitmap.lock();
+        bitmap.get_or_insert_with(IoBitmap::boxed).allow(io_addr, len);
+        // SAFETY: the bitmap lock keeps this thread on its CPU
+        unsafe { x86_load_io_bitmap(bitmap.as_deref()) };
+        ok_to_ret(0)
+    }
 
-    log_warn!("sys_ioports_request: not implemented (stub)");
+    #[cfg(not(target_arch = "x86_64"))]
     err_to_ret(RX_ERR_NOT_SUPPORTED)
 }
 
@@ -430,9 +557,10 @@ pub fn sys_pc_firmware_tables_impl(
 ///
 /// # Arguments
 ///
-/// * `iommu_handle` - IOMMU handle
+/// * `iommu_handle` - IOMMU handle, or 0 for a dummy IOMMU
 /// * `options` - Options (must be 0)
-/// * `bti_id` - BTI ID
+/// * `bti_id` - Bus transaction ID of the device (PCI segment, bus,
+///   device and function for PCI devices)
 /// * `bti_out` - User pointer to store BTI handle
 ///
 /// # Returns
@@ -457,18 +585,30 @@ pub fn sys_bti_create_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Get IOMMU from handle
-    // TODO: Create BTI
-    // TODO: Return BTI handle
+    let driver: Arc<dyn Iommu> = if iommu_handle == 0 {
+        if iommu_object::hardware_enabled() {
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+        Arc::new(DummyIommu::new())
+    } else {
+        match iommu_object::get(iommu_handle as u64) {
+            Ok(iommu) => iommu.driver(),
+            Err(_) => return err_to_ret(RX_ERR_BAD_HANDLE),
+        }
+    };
 
-    // For now, return a stub handle
-    let bti_handle = 3000u32;
-    let user_ptr = UserPtr::<u8>::new(bti_out);
-    unsafe {
-        if let Err(err) = copy_to_user(user_ptr, &bti_handle as *const u32 as *const u8, 4) {
-            log_error!("sys_bti_create: copy_to_user failed: {:?}", err);
-            return err_to_ret(err.into());
+    let bti_handle = match bti::create(driver, bti_id) {
+        Ok(id) => id as u32,
+        Err(err) => {
+            log_error!("sys_bti_create: failed to create BTI: {:?}", err);
+            return err_to_ret(err);
         }
+    };
+
+    if let Err(err) = write_user(bti_out, &bti_handle) {
+        let _ = bti::destroy(bti_handle as BtiId);
+        log_error!("sys_bti_create: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
     }
 
     log_debug!("sys_bti_create: success handle={:#x}", bti_handle);
@@ -479,8 +619,33 @@ pub fn sys_bti_create_impl(
 /// BTI Pin
 /// ============================================================================
 
+/// Output buffers for `rx_bti_pin`
+///
+/// The syscall ABI only has six argument registers, so the outputs are
+/// passed through one user struct.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BtiPinBuffers {
+    /// User pointer to an array of u64 device addresses
+    pub addrs: usize,
+
+    /// Number of entries in `addrs`
+    pub addrs_count: usize,
+
+    /// User pointer to store the PMT handle
+    pub pmt_out: usize,
+}
+
 /// Pin VMO for DMA
 ///
+/// The pages stay pinned until the returned PMT is unpinned; closing its
+/// handle instead quarantines them (see [`pmt`]).
+///
+/// With `CONTIGUOUS`, the pages must be contiguous in device address
+/// space and only the first address is returned. Behind a translating
+/// IOMMU they always are; with a dummy IOMMU the VMO must be physically
+/// contiguous.
+///
 /// # Arguments
 ///
 /// * `bti_handle` - BTI handle
@@ -488,7 +653,7 @@ pub fn sys_bti_create_impl(
 /// * `vmo_handle` - VMO handle
 /// * `offset` - Offset in VMO
 /// * `size` - Size to pin
-/// * `addrs_out` - User pointer to store physical addresses
+/// * `addrs_out` - User pointer to store device addresses
 /// * `addrs_count` - Number of addresses
 /// * `pmt_out` - User pointer to store PMT handle
 ///
@@ -515,16 +680,72 @@ pub fn sys_bti_pin_impl(
         size
     );
 
-    // TODO: Get BTI from handle
-    // TODO: Validate offset and size are page-aligned
-    // TODO: Get VMO from handle and validate rights
-    // TODO: Parse options and validate permissions
-    // TODO: Pin VMO
-    // TODO: Encode addresses
-    // TODO: Return addresses and PMT handle
+    TOTAL_BTI_OPS.fetch_add(1, Ordering::Relaxed);
 
-    log_warn!("sys_bti_pin: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    let bti = match lookup_bti(bti_handle) {
+        Ok(bti) => bti,
+        Err(err) => return err_to_ret(err),
+    };
+
+    if size == 0 || (offset & 0xFFF) != 0 || (size & 0xFFF) != 0 {
+        log_error!("sys_bti_pin: offset and size must be page-aligned");
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    if options & (bti_perm::READ | bti_perm::WRITE) == 0 || options & bti_perm::COMPRESS != 0 {
+        log_error!("sys_bti_pin: unsupported options {:#x}", options);
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    let contiguous = options & bti_perm::CONTIGUOUS != 0;
+    let expected = if contiguous { 1 } else { (size / 4096) as usize };
+    if addrs_count != expected {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    // The VMO handle needs MAP plus the access the device is given
+    let mut vmo_rights = Rights::MAP;
+    if options & bti_perm::READ != 0 {
+        vmo_rights = vmo_rights.add(Rights::READ);
+    }
+    if options & bti_perm::WRITE != 0 {
+        vmo_rights = vmo_rights.add(Rights::WRITE);
+    }
+    let vmo = match lookup_vmo_from_handle(vmo_handle, vmo_rights) {
+        Ok((vmo, _handle)) => vmo,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let perms = options & (bti_perm::READ | bti_perm::WRITE | bti_perm::EXECUTE);
+    let (pmt_id, mut addrs) = match pmt::pin(&bti, vmo, offset, size, perms) {
+        Ok(pinned) => pinned,
+        Err(err) => {
+            log_error!("sys_bti_pin: failed to pin: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    if contiguous {
+        if !bti::is_contiguous(&addrs) {
+            let _ = pmt::unpin(pmt_id);
+            return err_to_ret(RX_ERR_INVALID_ARGS);
+        }
+        addrs.truncate(1);
+    }
+
+    let pmt_handle = pmt_id as u32;
+    let user_ptr = UserPtr::<u8>::new(addrs_out);
+    let copied: Result = unsafe {
+        copy_to_user(user_ptr, addrs.as_ptr() as *const u8, addrs.len() * 8).map_err(|err| err.into())
+    };
+    if let Err(err) = copied.and_then(|_| write_user(pmt_out, &pmt_handle)) {
+        let _ = pmt::unpin(pmt_id);
+        log_error!("sys_bti_pin: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    log_debug!("sys_bti_pin: pinned {} addresses pmt={}", addrs.len(), pmt_handle);
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -533,6 +754,9 @@ pub fn sys_bti_pin_impl(
 
 /// Release BTI quarantine
 ///
+/// Unmaps the pins that were dropped without `rx_pmt_unpin`. The driver
+/// must have stopped the device first.
+///
 /// # Arguments
 ///
 /// * `bti_handle` - BTI handle
@@ -544,10 +768,13 @@ pub fn sys_bti_pin_impl(
 pub fn sys_bti_release_quarantine_impl(bti_handle: u32) -> SyscallRet {
     log_debug!("sys_bti_release_quarantine: bti={:#x}", bti_handle);
 
-    // TODO: Get BTI from handle
-    // TODO: Release quarantine
+    let bti = match lookup_bti(bti_handle) {
+        Ok(bti) => bti,
+        Err(err) => return err_to_ret(err),
+    };
 
-    log_warn!("sys_bti_release_quarantine: not implemented (stub)");
+    let released = bti.release_quarantine();
+    log_debug!("sys_bti_release_quarantine: released {} pins", released);
     ok_to_ret(0)
 }
 
@@ -568,11 +795,24 @@ pub fn sys_bti_release_quarantine_impl(bti_handle: u32) -> SyscallRet {
 pub fn sys_pmt_unpin_impl(pmt_handle: u32) -> SyscallRet {
     log_debug!("sys_pmt_unpin: pmt={:#x}", pmt_handle);
 
-    // TODO: Get PMT from handle
-    // TODO: Mark as unpinned
+    match pmt::unpin(pmt_handle as PmtId) {
+        Ok(()) => ok_to_ret(0),
+        Err(RX_ERR_NOT_FOUND) => err_to_ret(RX_ERR_BAD_HANDLE),
+        Err(err) => err_to_ret(err),
+    }
+}
 
-    log_warn!("sys_pmt_unpin: not implemented (stub)");
-    ok_to_ret(0)
+/// Whether `handle` is a PMT
+pub fn is_pmt_handle(handle: u32) -> bool {
+    pmt::get(handle as PmtId).is_ok()
+}
+
+/// Drop a handle to a PMT, destroying it
+///
+/// A PMT that is still pinned has its pages quarantined with a warning.
+/// Returns false if `handle` is not a PMT.
+pub fn close_pmt_handle(handle: u32) -> bool {
+    pmt::close(handle as PmtId)
 }
 
 /// ============================================================================
@@ -583,7 +823,8 @@ pub fn sys_pmt_unpin_impl(pmt_handle: u32) -> SyscallRet {
 ///
 /// # Arguments
 ///
-/// * `src_obj` - Resource handle (for physical interrupts)
+/// * `src_obj` - Root resource, or an IRQ resource covering `src_num` (for
+///   physical interrupts)
 /// * `src_num` - Interrupt number
 /// * `options` - Options
 /// * `handle_out` - User pointer to store interrupt handle
@@ -607,23 +848,30 @@ pub fn sys_interrupt_create_impl(
 
     // Resource not required for virtual interrupts
     if options & interrupt_flags::VIRTUAL == 0 {
-        // TODO: Validate resource handle for IRQ
-        log_debug!("sys_interrupt_create: physical interrupt");
-    } else {
-        log_debug!("sys_interrupt_create: virtual interrupt");
+        if let Err(err) = validate_ranged_resource(src_obj, ResourceKind::Irq, src_num as u64, 1) {
+            log_error!("sys_interrupt_create: invalid resource: {:?}", err);
+            return err_to_ret(err);
+        }
     }
 
-    // TODO: Create interrupt
-    // TODO: Return interrupt handle
+    if options & !(interrupt_flags::VIRTUAL | interrupt_flags::REMAPPED) != 0 {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
 
-    // For now, return a stub handle
-    let irq_handle = 4000u32;
-    let user_ptr = UserPtr::<u8>::new(handle_out);
-    unsafe {
-        if let Err(err) = copy_to_user(user_ptr, &irq_handle as *const u32 as *const u8, 4) {
-            log_error!("sys_interrupt_create: copy_to_user failed: {:?}", err);
-            return err_to_ret(err.into());
+    let flags = InterruptFlags::from_raw(options & interrupt_flags::VIRTUAL);
+    let irq_handle = match interrupt::create(src_num, flags) {
+        Ok(id) => id as u32,
+        Err(err) => {
+            log_error!("sys_interrupt_create: failed: {:?}", err);
+            return err_to_ret(err);
         }
+    };
+    TOTAL_INTERRUPTS.fetch_add(1, Ordering::Relaxed);
+
+    if let Err(err) = write_user(handle_out, &irq_handle) {
+        let _ = interrupt::destroy(irq_handle as u64);
+        log_error!("sys_interrupt_create: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
     }
 
     log_debug!("sys_interrupt_create: success handle={:#x}", irq_handle);
@@ -725,11 +973,11 @@ pub fn sys_interrupt_bind_vcpu_impl(
 pub fn sys_interrupt_ack_impl(handle: u32) -> SyscallRet {
     log_debug!("sys_interrupt_ack: handle={:#x}", handle);
 
-    // TODO: Get interrupt from handle
-    // TODO: Acknowledge interrupt
-
-    log_warn!("sys_interrupt_ack: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    match interrupt::get(handle as u64).and_then(|irq| irq.ack()) {
+        Ok(()) => ok_to_ret(0),
+        Err(RX_ERR_NOT_FOUND) => err_to_ret(RX_ERR_BAD_HANDLE),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -750,12 +998,22 @@ pub fn sys_interrupt_ack_impl(handle: u32) -> SyscallRet {
 pub fn sys_interrupt_wait_impl(handle: u32, timestamp_out: usize) -> SyscallRet {
     log_debug!("sys_interrupt_wait: handle={:#x}", handle);
 
-    // TODO: Get interrupt from handle
-    // TODO: Wait for interrupt
-    // TODO: Return timestamp
+    // TODO: Block until delivery once the scheduler supports it;
+    // callers retry on RX_ERR_SHOULD_WAIT
+    let timestamp = match interrupt::get(handle as u64).and_then(|irq| irq.wait()) {
+        Ok(timestamp) => timestamp,
+        Err(RX_ERR_NOT_FOUND) => return err_to_ret(RX_ERR_BAD_HANDLE),
+        Err(err) => return err_to_ret(err),
+    };
+
+    if timestamp_out != 0 {
+        if let Err(err) = write_user(timestamp_out, &timestamp) {
+            log_error!("sys_interrupt_wait: copy_to_user failed: {:?}", err);
+            return err_to_ret(err);
+        }
+    }
 
-    log_warn!("sys_interrupt_wait: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -775,11 +1033,10 @@ pub fn sys_interrupt_wait_impl(handle: u32, timestamp_out: usize) -> SyscallRet
 pub fn sys_interrupt_destroy_impl(handle: u32) -> SyscallRet {
     log_debug!("sys_interrupt_destroy: handle={:#x}", handle);
 
-    // TODO: Get interrupt from handle
-    // TODO: Destroy interrupt
-
-    log_warn!("sys_interrupt_destroy: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    match interrupt::destroy(handle as u64) {
+        Ok(()) => ok_to_ret(0),
+        Err(_) => err_to_ret(RX_ERR_BAD_HANDLE),
+    }
 }
 
 /// ============================================================================
@@ -814,11 +1071,11 @@ pub fn sys_interrupt_trigger_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Get interrupt from handle
-    // TODO: Trigger interrupt
-
-    log_warn!("sys_interrupt_trigger: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    match interrupt::get(handle as u64).and_then(|irq| irq.trigger(timestamp)) {
+        Ok(()) => ok_to_ret(0),
+        Err(RX_ERR_NOT_FOUND) => err_to_ret(RX_ERR_BAD_HANDLE),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -880,10 +1137,10 @@ pub fn sys_smc_call_impl(
 /// Get DDK statistics
 pub fn get_stats() -> DdkStats {
     DdkStats {
-        total_vmo_contiguous: 0,  // TODO: Track
-        total_vmo_physical: 0,    // TODO: Track
-        total_interrupts: 0,      // TODO: Track
-        total_bti_ops: 0,         // TODO: Track
+        total_vmo_contiguous: TOTAL_VMO_CONTIGUOUS.load(Ordering::Relaxed),
+        total_vmo_physical: TOTAL_VMO_PHYSICAL.load(Ordering::Relaxed),
+        total_interrupts: TOTAL_INTERRUPTS.load(Ordering::Relaxed),
+        total_bti_ops: TOTAL_BTI_OPS.load(Ordering::Relaxed),
     }
 }
 
@@ -911,7 +1168,6 @@ pub struct DdkStats {
 /// Initialize the DDK subsystem
 pub fn init() {
     log_info!("DDK subsystem initialized");
-    log_info!("  IOMMU max descriptor: {}", IOMMU_MAX_DESC_LEN);
 }
 
 /// ============================================================================
@@ -939,6 +1195,7 @@ mod tests {
 
     #[test]
     fn test_iommu_type_consts() {
+        assert_eq!(iommu_type::DUMMY, 0);
         assert_eq!(iommu_type::INTEL, 1);
         assert_eq!(iommu_type::ARM_SMMU, 2);
         assert_eq!(iommu_type::BROADCOM, 3);
@@ -955,4 +1212,64 @@ mod tests {
         let result = sys_vmo_create_contiguous_impl(0, 4096, 64, 0);
         assert!(result < 0);
     }
+
+    #[test]
+    fn test_bti_pin_rejects_unaligned() {
+        let bti_handle = bti::create(Arc::new(DummyIommu::new()), 0).unwrap() as u32;
+        assert_eq!(
+            sys_bti_pin_impl(bti_handle, bti_perm::READ, 1, 0x800, 0x1000, 0, 1, 0),
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            sys_bti_pin_impl(bti_handle, 0, 1, 0, 0x1000, 0, 1, 0),
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            sys_bti_pin_impl(bti_handle, bti_perm::READ, 1, 0, 0x2000, 0, 1, 0),
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        );
+        bti::destroy(bti_handle as BtiId).unwrap();
+    }
+
+    #[test]
+    fn test_bti_bad_handle() {
+        assert_eq!(
+            sys_bti_pin_impl(u32::MAX, bti_perm::READ, 1, 0, 0x1000, 0, 1, 0),
+            err_to_ret(RX_ERR_BAD_HANDLE)
+        );
+        assert_eq!(sys_bti_release_quarantine_impl(u32::MAX), err_to_ret(RX_ERR_BAD_HANDLE));
+    }
+
+    #[test]
+    fn test_pmt_unpin_unknown() {
+        assert_eq!(sys_pmt_unpin_impl(1), err_to_ret(RX_ERR_BAD_HANDLE));
+    }
+
+    #[test]
+    fn test_resource_enforcement() {
+        let root = resource::test_root_handle();
+        let uart = resource::create(root, resource::resource_kind::IOPORT, 0, 0x3f8, 8, "com1".into())
+            .unwrap();
+        let irq = resource::create(root, resource::resource_kind::IRQ, 0, 36, 1, "irq".into())
+            .unwrap();
+
+        // Outside the resource's range, or the wrong kind
+        assert_eq!(
+            sys_ioports_request_impl(uart, 0x2f8, 8),
+            err_to_ret(RX_ERR_OUT_OF_RANGE)
+        );
+        assert_eq!(
+            sys_ioports_request_impl(irq, 0x3f8, 8),
+            err_to_ret(RX_ERR_ACCESS_DENIED)
+        );
+        assert_eq!(sys_ioports_request_impl(root, 0xfff8, 0x10), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(
+            sys_vmo_create_physical_impl(uart, 0xfe00_0000, 0x1000, 0),
+            err_to_ret(RX_ERR_ACCESS_DENIED)
+        );
+        assert_eq!(
+            sys_interrupt_create_impl(irq, 37, 0, 0),
+            err_to_ret(RX_ERR_OUT_OF_RANGE)
+        );
+    }
 }
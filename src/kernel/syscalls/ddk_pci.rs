@@ -24,8 +24,13 @@
 //! - `rx_pci_set_irq_mode` - Set IRQ mode
 
 
+use crate::kernel::dev::pcie::bus::{self, BusDevice};
+use crate::kernel::dev::pcie::msi::PciIrqMode;
+use crate::kernel::dev::pcie::PciAddrSpace;
+use crate::kernel::object::vmo::Vmo;
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::system::{validate_resource, ResourceKind};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
@@ -77,6 +82,14 @@ pub const PCIE_BASE_CONFIG_SIZE: u16 = 256;
 /// Maximum BAR registers
 pub const PCIE_MAX_BAR_REGS: u32 = 6;
 
+/// ============================================================================
+/// Statistics Counters
+/// ============================================================================
+
+static TOTAL_PCI_INIT: AtomicU64 = AtomicU64::new(0);
+static TOTAL_CONFIG_OPS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_IRQ_OPS: AtomicU64 = AtomicU64::new(0);
+
 /// ============================================================================
 /// PCI IRQ Modes
 /// ============================================================================
@@ -226,6 +239,52 @@ pub struct PciInitArg {
     // pub dev_pin_to_global_irq: [u32; 32][8][4]
 }
 
+/// ============================================================================
+/// Device Handles
+/// ============================================================================
+
+/// Run `f` on the device named by a PCI device handle
+///
+/// Device handles are placeholders until process handle tables are wired
+/// up: the value is the device's enumeration index plus one, so 0 never
+/// names a device.
+fn with_pci_device<R>(handle: u32, f: impl FnOnce(&mut BusDevice) -> Result<R>) -> Result<R> {
+    if handle == 0 {
+        return Err(RX_ERR_BAD_HANDLE);
+    }
+    bus::with_device(handle as usize - 1, f).map_err(|_| RX_ERR_BAD_HANDLE)?
+}
+
+/// Copy a plain value out to user memory
+fn write_user<T: Copy>(ptr: usize, value: &T) -> Result {
+    let user_ptr = UserPtr::<u8>::new(ptr);
+    unsafe {
+        copy_to_user(user_ptr, value as *const T as *const u8, core::mem::size_of::<T>())
+            .map_err(|err| err.into())
+    }
+}
+
+/// Build the userspace view of a device
+fn device_info(dev: &BusDevice) -> PciDeviceInfo {
+    let info = &dev.info;
+    let mut irqs = [PCI_NO_IRQ_MAPPING; 6];
+    if info.irq_pin != 0 {
+        irqs[0] = info.irq_line as u32;
+    }
+    PciDeviceInfo {
+        vendor_id: info.vendor_id,
+        device_id: info.device_id,
+        base_class: info.class_code.0,
+        sub_class: info.class_code.1,
+        prog_if: info.class_code.2,
+        revision_id: info.revision_id,
+        bus_id: info.addr.bus,
+        dev_id: info.addr.device,
+        func_id: info.addr.function,
+        irqs,
+    }
+}
+
 /// ============================================================================
 /// Syscall: PCI Add/Subtract IO Range
 /// ============================================================================
@@ -309,6 +368,9 @@ pub fn sys_pci_init_impl(
     // TODO: Add root complex
     // TODO: Start bus driver
 
+    TOTAL_PCI_INIT.fetch_add(1, Ordering::Relaxed);
+
+    // The kernel enumerates ECAM itself from ACPI / the device tree
     log_info!("sys_pci_init: PCI init stub");
     ok_to_ret(0)
 }
@@ -342,12 +404,23 @@ pub fn sys_pci_get_nth_device_impl(
         index
     );
 
-    // TODO: Validate resource handle
-    // TODO: Get PCI device by index
-    // TODO: Return device info and handle
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_pci_get_nth_device: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
 
-    log_warn!("sys_pci_get_nth_device: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    let info = match with_pci_device(index.wrapping_add(1), |dev| Ok(device_info(dev))) {
+        Ok(info) => info,
+        Err(_) => return err_to_ret(RX_ERR_OUT_OF_RANGE),
+    };
+
+    let dev_handle = index + 1;
+    if let Err(err) = write_user(info_out, &info).and_then(|_| write_user(handle_out, &dev_handle)) {
+        log_error!("sys_pci_get_nth_device: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -385,12 +458,19 @@ pub fn sys_pci_config_read_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Get PCI device from handle
-    // TODO: Read config space
-    // TODO: Return value
+    TOTAL_CONFIG_OPS.fetch_add(1, Ordering::Relaxed);
 
-    log_warn!("sys_pci_config_read: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    let value = match with_pci_device(handle, |dev| dev.config_read(offset, width)) {
+        Ok(value) => value,
+        Err(err) => return err_to_ret(err),
+    };
+
+    if let Err(err) = write_user(val_out, &value) {
+        log_error!("sys_pci_config_read: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -429,12 +509,18 @@ pub fn sys_pci_config_write_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Get PCI device from handle
-    // TODO: Validate offset (not in standard header)
-    // TODO: Write config space
+    // BARs, command and interrupt routing belong to the kernel
+    if offset < PCI_STANDARD_CONFIG_HDR_SIZE {
+        log_error!("sys_pci_config_write: offset {:#x} is in the standard header", offset);
+        return err_to_ret(RX_ERR_ACCESS_DENIED);
+    }
 
-    log_warn!("sys_pci_config_write: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    TOTAL_CONFIG_OPS.fetch_add(1, Ordering::Relaxed);
+
+    match with_pci_device(handle, |dev| dev.config_write(offset, width, val)) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -520,11 +606,13 @@ pub fn sys_pci_enable_bus_master_impl(
         enable
     );
 
-    // TODO: Get PCI device from handle
-    // TODO: Enable/disable bus master
-
-    log_warn!("sys_pci_enable_bus_master: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    match with_pci_device(dev_handle, |dev| {
+        dev.set_bus_master(enable);
+        Ok(())
+    }) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -585,13 +673,47 @@ pub fn sys_pci_get_bar_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Get PCI device from handle
-    // TODO: Get BAR info
-    // TODO: Create VMO for MMIO BARs
-    // TODO: Return BAR info and handle
+    let bar = match with_pci_device(dev_handle, |dev| dev.bar(bar_num as usize).ok_or(RX_ERR_NOT_FOUND)) {
+        Ok(bar) => bar,
+        Err(err) => return err_to_ret(err),
+    };
+    if bar.base == 0 {
+        log_error!("sys_pci_get_bar: BAR{} is unassigned", bar_num);
+        return err_to_ret(RX_ERR_BAD_STATE);
+    }
 
-    log_warn!("sys_pci_get_bar: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    let mut info = PciBar { size: bar.size, bar_type: pci_bar_type::PIO, reserved: 0, addr: 0 };
+
+    match bar.addr_space {
+        PciAddrSpace::PIO => {
+            info.addr = bar.base as u32;
+        }
+        PciAddrSpace::MMIO => {
+            // Small BARs still occupy a whole page of the VMO
+            let size = ((bar.size + 0xFFF) & !0xFFF) as usize;
+            let vmo_handle = match Vmo::create_physical(bar.base & !0xFFF, size)
+                .and_then(crate::kernel::syscalls::vmo::register_vmo)
+            {
+                Ok(handle) => handle,
+                Err(err) => {
+                    log_error!("sys_pci_get_bar: failed to create VMO: {:?}", err);
+                    return err_to_ret(err);
+                }
+            };
+            info.bar_type = pci_bar_type::MMIO;
+            if let Err(err) = write_user(handle_out, &vmo_handle) {
+                log_error!("sys_pci_get_bar: copy_to_user failed: {:?}", err);
+                return err_to_ret(err);
+            }
+        }
+    }
+
+    if let Err(err) = write_user(bar_out, &info) {
+        log_error!("sys_pci_get_bar: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -621,12 +743,23 @@ pub fn sys_pci_map_interrupt_impl(
         which_irq
     );
 
-    // TODO: Get PCI device from handle
-    // TODO: Map interrupt
-    // TODO: Return interrupt handle
+    TOTAL_IRQ_OPS.fetch_add(1, Ordering::Relaxed);
 
-    log_warn!("sys_pci_map_interrupt: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    // -1 selects the legacy interrupt
+    let which = if which_irq < 0 { 0 } else { which_irq as u32 };
+    let irq = match with_pci_device(dev_handle, |dev| dev.map_interrupt(which)) {
+        Ok(irq) => irq,
+        Err(err) => return err_to_ret(err),
+    };
+
+    // TODO: Create a bound interrupt object once they exist
+    // For now, return the platform IRQ number as the handle value
+    if let Err(err) = write_user(handle_out, &irq) {
+        log_error!("sys_pci_map_interrupt: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -656,12 +789,22 @@ pub fn sys_pci_query_irq_mode_impl(
         mode
     );
 
-    // TODO: Get PCI device from handle
-    // TODO: Query IRQ mode capabilities
-    // TODO: Return max IRQs
+    let mode = match PciIrqMode::from_raw(mode) {
+        Some(mode) => mode,
+        None => return err_to_ret(RX_ERR_INVALID_ARGS),
+    };
 
-    log_warn!("sys_pci_query_irq_mode: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    let max_irqs = match with_pci_device(dev_handle, |dev| dev.query_irq_mode(mode)) {
+        Ok(max) => max,
+        Err(err) => return err_to_ret(err),
+    };
+
+    if let Err(err) = write_user(max_irqs_out, &max_irqs) {
+        log_error!("sys_pci_query_irq_mode: copy_to_user failed: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -692,11 +835,17 @@ pub fn sys_pci_set_irq_mode_impl(
         requested_irq_count
     );
 
-    // TODO: Get PCI device from handle
-    // TODO: Set IRQ mode
+    let mode = match PciIrqMode::from_raw(mode) {
+        Some(mode) => mode,
+        None => return err_to_ret(RX_ERR_INVALID_ARGS),
+    };
 
-    log_warn!("sys_pci_set_irq_mode: not implemented (stub)");
-    err_to_ret(RX_ERR_NOT_SUPPORTED)
+    TOTAL_IRQ_OPS.fetch_add(1, Ordering::Relaxed);
+
+    match with_pci_device(dev_handle, |dev| dev.set_irq_mode(mode, requested_irq_count)) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -706,10 +855,10 @@ pub fn sys_pci_set_irq_mode_impl(
 /// Get PCI DDK statistics
 pub fn get_stats() -> PciStats {
     PciStats {
-        total_pci_init: 0,      // TODO: Track
-        total_config_ops: 0,    // TODO: Track
-        total_irq_ops: 0,       // TODO: Track
-        total_devices: 0,       // TODO: Track
+        total_pci_init: TOTAL_PCI_INIT.load(Ordering::Relaxed),
+        total_config_ops: TOTAL_CONFIG_OPS.load(Ordering::Relaxed),
+        total_irq_ops: TOTAL_IRQ_OPS.load(Ordering::Relaxed),
+        total_devices: bus::device_count() as u64,
     }
 }
 
@@ -777,6 +926,11 @@ mod tests {
         assert_eq!(PCI_NO_IRQ_MAPPING, 0xFFFFFFFF);
     }
 
+    #[test]
+    fn test_device_handle_zero_is_invalid() {
+        assert_eq!(with_pci_device(0, |_| Ok(())), Err(RX_ERR_BAD_HANDLE));
+    }
+
     #[test]
     fn test_pci_device_info_size() {
         assert_eq!(core::mem::size_of::<PciDeviceInfo>(), 20);
@@ -16,9 +16,21 @@
 //! - `rx_ktrace_read` - Read kernel trace data
 //! - `rx_ktrace_control` - Control kernel tracing
 //! - `rx_ktrace_write` - Write to kernel trace
+//! - `rx_profiler_control` - Control the sampling profiler
+//! - `rx_profiler_read` - Read profiler samples
+//! - `rx_perfmon_control` - Configure the performance counters
+//! - `rx_perfmon_read` - Read performance counter totals
+//! - `rx_itrace_control` - Control hardware instruction trace
+//! - `rx_itrace_get_buffer` - Hand over an instruction trace buffer
 //! - `rx_mtrace_control` - Control memory tracing
 
 
+use crate::kernel::lib::itrace::{self, ItraceConfig};
+use crate::kernel::lib::ktrace;
+use crate::kernel::lib::perfmon::{self, PerfmonConfig, PerfmonCounts, PerfmonProperties};
+use crate::kernel::lib::profiler;
+use crate::kernel::syscalls::system::{validate_resource, ResourceKind};
+use crate::kernel::syscalls::vmo::register_shared_vmo;
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
@@ -34,9 +46,21 @@ use crate::{log_debug, log_error, log_info};
 /// Maximum debug write size
 const MAX_DEBUG_WRITE_SIZE: usize = 256;
 
+/// Most bytes `rx_debug_read` returns at once
+const MAX_DEBUG_READ_SIZE: usize = 256;
+
 /// Maximum name length
 const MAX_NAME_LEN: usize = 32;
 
+/// Bounce buffer for `rx_ktrace_read`, a whole number of trace records
+const KTRACE_CHUNK_SIZE: usize = 16 * ktrace::KtraceRecord::SIZE;
+
+/// Bounce buffer for `rx_profiler_read`, a whole number of samples
+const PROFILER_CHUNK_SIZE: usize = 8 * profiler::ProfilerSample::SIZE;
+
+/// Bounce buffer for `rx_perfmon_read`, in entries
+const PERFMON_CHUNK_ENTRIES: usize = 16;
+
 /// ============================================================================
 /// KTrace Constants
 /// ============================================================================
@@ -56,6 +80,45 @@ pub mod ktrace_action {
     pub const RESET: u32 = 3;
 }
 
+/// Profiler actions
+pub mod profiler_action {
+    /// Start sampling; the options are the period in microseconds
+    pub const START: u32 = 1;
+
+    /// Stop sampling
+    pub const STOP: u32 = 2;
+
+    /// Discard the samples
+    pub const RESET: u32 = 3;
+}
+
+/// Perfmon actions
+pub mod perfmon_action {
+    /// Copy out the `PerfmonProperties`
+    pub const GET_PROPERTIES: u32 = 0;
+
+    /// Start counting with the `PerfmonConfig` passed in
+    pub const START: u32 = 1;
+
+    /// Stop counting
+    pub const STOP: u32 = 2;
+}
+
+/// Itrace actions
+pub mod itrace_action {
+    /// Allocate buffers for the `ItraceConfig` passed in
+    pub const ALLOC: u32 = 0;
+
+    /// Free the buffers
+    pub const FREE: u32 = 1;
+
+    /// Start tracing
+    pub const START: u32 = 2;
+
+    /// Stop tracing and flush the trace
+    pub const STOP: u32 = 3;
+}
+
 /// ============================================================================
 /// MTrace Constants
 /// ============================================================================
@@ -78,73 +141,66 @@ pub mod mtrace_kind {
 
 /// Read from debug console
 ///
+/// Drains whatever the debug UART has received without waiting for more.
+/// The kernel debug shell (`kernel.shell=true`) polls the same UART, so
+/// with it enabled each byte goes to whichever reader gets there first.
+///
 /// # Arguments
 ///
 /// * `handle` - Resource handle (must be root resource)
 /// * `buffer` - User pointer to buffer
-/// * `len` - User pointer to length (in/out)
+/// * `buffer_size` - Size of the buffer
+/// * `actual` - User pointer to store the number of bytes read
 ///
 /// # Returns
 ///
 /// * On success: 0
-/// * On error: Negative error code
-pub fn sys_debug_read_impl(handle: u32, buffer: usize, len: usize) -> SyscallRet {
+/// * On error: Negative error code, SHOULD_WAIT if nothing was typed
+pub fn sys_debug_read_impl(handle: u32, buffer: usize, buffer_size: usize, actual: usize) -> SyscallRet {
     log_debug!(
-        "sys_debug_read: handle={:#x} buffer={:#x} len={:#x}",
+        "sys_debug_read: handle={:#x} buffer={:#x} size={}",
         handle,
         buffer,
-        len
+        buffer_size
     );
 
-    // TODO: Validate resource handle
-
-    // Get the requested length
-    let mut read_len = 0;
-    let len_ptr = UserPtr::<u8>::new(len);
-    unsafe {
-        if let Err(err) = copy_from_user(&mut read_len as *mut usize as *mut u8, len_ptr, 1) {
-            log_error!("sys_debug_read: copy_from_user len failed: {:?}", err);
-            return err_to_ret(err.into());
-        }
-    }
-
-    // Limit read size
-    if read_len > MAX_DEBUG_WRITE_SIZE {
-        read_len = MAX_DEBUG_WRITE_SIZE;
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_debug_read: invalid resource: {:?}", err);
+        return err_to_ret(err);
     }
 
-    // Read characters from console
-    let mut chars_read = 0;
-    let buffer_ptr = UserPtr::<u8>::new(buffer);
-
-    for i in 0..read_len {
-        // TODO: Implement actual console read
-        // For now, just fill with zeros
-        let c = 0u8;
-
-        unsafe {
-            if let Err(err) = copy_to_user(
-                UserPtr::<u8>::new(buffer + i),
-                &c as *const u8,
-                1,
-            ) {
-                log_error!("sys_debug_read: copy_to_user failed: {:?}", err);
-                return err_to_ret(err.into());
+    let mut chunk = [0u8; MAX_DEBUG_READ_SIZE];
+    let want = buffer_size.min(chunk.len());
+    let mut read = 0usize;
+    while read < want {
+        match crate::platform::platform_dgetc() {
+            Some(c) => {
+                chunk[read] = c;
+                read += 1;
             }
+            None => break,
         }
+    }
 
-        chars_read += 1;
+    if read == 0 && want > 0 {
+        return err_to_ret(RX_ERR_SHOULD_WAIT);
     }
 
-    // Write back actual length
     unsafe {
-        if let Err(err) = copy_to_user(len_ptr, &(chars_read as usize) as *const usize as *const u8, 1) {
-            log_error!("sys_debug_read: copy_to_user len failed: {:?}", err);
+        if let Err(err) = copy_to_user(UserPtr::<u8>::new(buffer), chunk.as_ptr(), read) {
+            log_error!("sys_debug_read: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+        if let Err(err) = copy_to_user(
+            UserPtr::<u8>::new(actual),
+            &read as *const usize as *const u8,
+            core::mem::size_of::<usize>(),
+        ) {
+            log_error!("sys_debug_read: copy_to_user actual failed: {:?}", err);
             return err_to_ret(err.into());
         }
     }
 
-    log_debug!("sys_debug_read: success, read {} bytes", chars_read);
     ok_to_ret(0)
 }
 
@@ -252,20 +308,21 @@ pub fn sys_debug_send_command_impl(
     buf[len] = b'\n';
     buf[len + 1] = 0;
 
-    // Convert to string
+    // Convert to string and run it as a shell command line
     let str_result = alloc::str::from_utf8(&buf[..len]);
     match str_result {
         Ok(s) => {
             log_info!("DEBUG COMMAND: {}", s.trim());
+            for line in s.lines() {
+                crate::kernel::lib::console::run_command(line);
+            }
         }
         Err(_) => {
             log_info!("DEBUG COMMAND: {:x?}", &buf[..len]);
+            return err_to_ret(RX_ERR_INVALID_ARGS);
         }
     }
 
-    // TODO: Execute console command
-    // console_run_script(buf.as_ptr());
-
     log_debug!("sys_debug_send_command: success");
     ok_to_ret(0)
 }
@@ -279,10 +336,11 @@ pub fn sys_debug_send_command_impl(
 /// # Arguments
 ///
 /// * `handle` - Resource handle (must be root resource)
-/// * `data` - User pointer to data buffer
-/// * `offset` - Offset in trace buffer
+/// * `data` - User pointer to data buffer, or 0 to query the trace size
+/// * `offset` - Byte offset in the trace
 /// * `len` - Length to read
-/// * `actual` - User pointer to store actual bytes read
+/// * `actual` - User pointer to store actual bytes read, or the trace
+///   size when `data` is 0
 ///
 /// # Returns
 ///
@@ -303,20 +361,49 @@ pub fn sys_ktrace_read_impl(
         len
     );
 
-    // TODO: Validate resource handle
-    // TODO: Implement actual ktrace read
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_ktrace_read: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let actual_len = if data == 0 {
+        ktrace::size()
+    } else {
+        // Copy out through a bounce buffer of whole records
+        let mut chunk = [0u8; KTRACE_CHUNK_SIZE];
+        let mut copied = 0usize;
+        while copied < len {
+            let want = (len - copied).min(chunk.len());
+            let n = ktrace::read(offset as usize + copied, &mut chunk[..want]);
+            if n == 0 {
+                break;
+            }
+
+            let user_ptr = UserPtr::<u8>::new(data + copied);
+            unsafe {
+                if let Err(err) = copy_to_user(user_ptr, chunk.as_ptr(), n) {
+                    log_error!("sys_ktrace_read: copy_to_user data failed: {:?}", err);
+                    return err_to_ret(err.into());
+                }
+            }
+            copied += n;
+        }
+        copied
+    };
 
-    // For now, return zero bytes read
-    let actual_len = 0usize;
     let actual_ptr = UserPtr::<u8>::new(actual);
     unsafe {
-        if let Err(err) = copy_to_user(actual_ptr, &actual_len as *const usize as *const u8, 1) {
+        if let Err(err) = copy_to_user(
+            actual_ptr,
+            &actual_len as *const usize as *const u8,
+            core::mem::size_of::<usize>(),
+        ) {
             log_error!("sys_ktrace_read: copy_to_user actual failed: {:?}", err);
             return err_to_ret(err.into());
         }
     }
 
-    log_debug!("sys_ktrace_read: success, read 0 bytes");
+    log_debug!("sys_ktrace_read: success, {} bytes", actual_len);
     ok_to_ret(0)
 }
 
@@ -330,12 +417,12 @@ pub fn sys_ktrace_read_impl(
 ///
 /// * `handle` - Resource handle (must be root resource)
 /// * `action` - Action to perform
-/// * `options` - Options for the action
-/// * `ptr` - User pointer to options data
+/// * `options` - Group mask for `START` (0 traces every group)
+/// * `ptr` - User pointer to the probe name for `NEW_PROBE`
 ///
 /// # Returns
 ///
-/// * On success: 0
+/// * On success: 0, or the probe id for `NEW_PROBE`
 /// * On error: Negative error code
 pub fn sys_ktrace_control_impl(
     handle: u32,
@@ -350,12 +437,15 @@ pub fn sys_ktrace_control_impl(
         options
     );
 
-    // TODO: Validate resource handle
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_ktrace_control: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
 
     match action {
         ktrace_action::NEW_PROBE => {
             // Read probe name
-            let mut name_buf = alloc::vec![0u8; MAX_NAME_LEN];
+            let mut name_buf = [0u8; MAX_NAME_LEN];
             let user_ptr = UserPtr::<u8>::new(ptr);
 
             unsafe {
@@ -369,30 +459,24 @@ pub fn sys_ktrace_control_impl(
                 }
             }
 
-            // Null terminate
-            if let Some(pos) = name_buf.iter().position(|&c| c == 0) {
-                name_buf.truncate(pos);
+            match ktrace::add_probe(&name_buf) {
+                Ok(id) => ok_to_ret(id as usize),
+                Err(err) => err_to_ret(err),
             }
-
-            let name = alloc::string::String::from_utf8_lossy(&name_buf);
-            log_info!("ktrace: new probe '{}'", name);
-
-            // TODO: Implement actual ktrace control
-            ok_to_ret(0)
         }
 
         ktrace_action::START => {
-            log_info!("ktrace: start");
+            ktrace::start(if options == 0 { ktrace::KTRACE_GRP_ALL } else { options });
             ok_to_ret(0)
         }
 
         ktrace_action::STOP => {
-            log_info!("ktrace: stop");
+            ktrace::stop();
             ok_to_ret(0)
         }
 
         ktrace_action::RESET => {
-            log_info!("ktrace: reset");
+            ktrace::rewind();
             ok_to_ret(0)
         }
 
@@ -412,7 +496,7 @@ pub fn sys_ktrace_control_impl(
 /// # Arguments
 ///
 /// * `handle` - Resource handle (must be root resource)
-/// * `event_id` - Event ID (max 0x7FF)
+/// * `event_id` - Probe id from `NEW_PROBE` (max 0x7FF)
 /// * `arg0` - Argument 0
 /// * `arg1` - Argument 1
 ///
@@ -434,16 +518,429 @@ pub fn sys_ktrace_write_impl(
         arg1
     );
 
-    // TODO: Validate resource handle
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_ktrace_write: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
 
     // Validate event ID
-    if event_id > 0x7FF {
+    if event_id > ktrace::KTRACE_MAX_PROBE as u32 {
         log_error!("sys_ktrace_write: invalid event_id {:#x}", event_id);
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Implement actual ktrace write
-    log_debug!("ktrace: write event={:#x} args=({}, {})", event_id, arg0, arg1);
+    ktrace::write(ktrace::TAG_PROBE, event_id as u16, arg0 as u64, arg1 as u64);
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: Profiler Control
+/// ============================================================================
+
+/// Control the sampling profiler
+///
+/// # Arguments
+///
+/// * `handle` - Resource handle (must be root resource)
+/// * `action` - Action to perform
+/// * `options` - Sampling period in microseconds for `START` (0 for the
+///   default)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_profiler_control_impl(handle: u32, action: u32, options: u32) -> SyscallRet {
+    log_debug!(
+        "sys_profiler_control: handle={:#x} action={} options={}",
+        handle,
+        action,
+        options
+    );
+
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_profiler_control: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    match action {
+        profiler_action::START => {
+            if !profiler::available() {
+                return err_to_ret(RX_ERR_NOT_SUPPORTED);
+            }
+            profiler::start(options);
+            ok_to_ret(0)
+        }
+
+        profiler_action::STOP => {
+            profiler::stop();
+            ok_to_ret(0)
+        }
+
+        profiler_action::RESET => {
+            profiler::rewind();
+            ok_to_ret(0)
+        }
+
+        _ => {
+            log_error!("sys_profiler_control: invalid action {}", action);
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        }
+    }
+}
+
+/// ============================================================================
+/// Syscall: Profiler Read
+/// ============================================================================
+
+/// Read profiler samples
+///
+/// # Arguments
+///
+/// * `handle` - Resource handle (must be root resource)
+/// * `data` - User pointer to data buffer, or 0 to query the size
+/// * `offset` - Byte offset in the samples
+/// * `len` - Length to read
+/// * `actual` - User pointer to store actual bytes read, or the size of
+///   the samples when `data` is 0
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_profiler_read_impl(
+    handle: u32,
+    data: usize,
+    offset: usize,
+    len: usize,
+    actual: usize,
+) -> SyscallRet {
+    log_debug!(
+        "sys_profiler_read: handle={:#x} data={:#x} offset={} len={}",
+        handle,
+        data,
+        offset,
+        len
+    );
+
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_profiler_read: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let actual_len = if data == 0 {
+        profiler::size()
+    } else {
+        let mut chunk = [0u8; PROFILER_CHUNK_SIZE];
+        let mut copied = 0usize;
+        while copied < len {
+            let want = (len - copied).min(chunk.len());
+            let n = profiler::read(offset + copied, &mut chunk[..want]);
+            if n == 0 {
+                break;
+            }
+
+            let user_ptr = UserPtr::<u8>::new(data + copied);
+            unsafe {
+                if let Err(err) = copy_to_user(user_ptr, chunk.as_ptr(), n) {
+                    log_error!("sys_profiler_read: copy_to_user data failed: {:?}", err);
+                    return err_to_ret(err.into());
+                }
+            }
+            copied += n;
+        }
+        copied
+    };
+
+    let actual_ptr = UserPtr::<u8>::new(actual);
+    unsafe {
+        if let Err(err) = copy_to_user(
+            actual_ptr,
+            &actual_len as *const usize as *const u8,
+            core::mem::size_of::<usize>(),
+        ) {
+            log_error!("sys_profiler_read: copy_to_user actual failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: Perfmon Control
+/// ============================================================================
+
+/// Configure the performance counters
+///
+/// # Arguments
+///
+/// * `handle` - Resource handle (must be root resource)
+/// * `action` - Action to perform
+/// * `buffer` - User pointer to a `PerfmonProperties` for
+///   `GET_PROPERTIES` or a `PerfmonConfig` for `START`
+/// * `buffer_size` - Size of the buffer
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_perfmon_control_impl(handle: u32, action: u32, buffer: usize, buffer_size: usize) -> SyscallRet {
+    log_debug!(
+        "sys_perfmon_control: handle={:#x} action={} buffer={:#x} size={}",
+        handle,
+        action,
+        buffer,
+        buffer_size
+    );
+
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_perfmon_control: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    match action {
+        perfmon_action::GET_PROPERTIES => {
+            let size = core::mem::size_of::<PerfmonProperties>();
+            if buffer_size < size {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
+            let props = perfmon::properties();
+            unsafe {
+                if let Err(err) = copy_to_user(UserPtr::<u8>::new(buffer), &props as *const _ as *const u8, size) {
+                    log_error!("sys_perfmon_control: copy_to_user failed: {:?}", err);
+                    return err_to_ret(err.into());
+                }
+            }
+            ok_to_ret(0)
+        }
+
+        perfmon_action::START => {
+            let size = core::mem::size_of::<PerfmonConfig>();
+            if buffer_size != size {
+                return err_to_ret(RX_ERR_INVALID_ARGS);
+            }
+            let mut config = PerfmonConfig::default();
+            unsafe {
+                if let Err(err) = copy_from_user(&mut config as *mut _ as *mut u8, UserPtr::<u8>::new(buffer), size) {
+                    log_error!("sys_perfmon_control: copy_from_user failed: {:?}", err);
+                    return err_to_ret(err.into());
+                }
+            }
+            match perfmon::start(&config) {
+                Ok(()) => ok_to_ret(0),
+                Err(err) => err_to_ret(err),
+            }
+        }
+
+        perfmon_action::STOP => {
+            perfmon::stop();
+            ok_to_ret(0)
+        }
+
+        _ => {
+            log_error!("sys_perfmon_control: invalid action {}", action);
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        }
+    }
+}
+
+/// ============================================================================
+/// Syscall: Perfmon Read
+/// ============================================================================
+
+/// Read performance counter totals
+///
+/// Writes one `PerfmonCounts` per CPU, or one for the thread in thread
+/// mode.
+///
+/// # Arguments
+///
+/// * `handle` - Resource handle (must be root resource)
+/// * `buffer` - User pointer to an array of `PerfmonCounts`
+/// * `buffer_size` - Size of the buffer in bytes
+/// * `actual` - User pointer to store the number of entries written
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_perfmon_read_impl(handle: u32, buffer: usize, buffer_size: usize, actual: usize) -> SyscallRet {
+    log_debug!(
+        "sys_perfmon_read: handle={:#x} buffer={:#x} size={}",
+        handle,
+        buffer,
+        buffer_size
+    );
+
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_perfmon_read: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let entry_size = core::mem::size_of::<PerfmonCounts>();
+    let capacity = buffer_size / entry_size;
+    let mut chunk = [PerfmonCounts::default(); PERFMON_CHUNK_ENTRIES];
+    let mut written = 0usize;
+    while written < capacity {
+        let want = (capacity - written).min(chunk.len());
+        let n = perfmon::read(written, &mut chunk[..want]);
+        if n == 0 {
+            break;
+        }
+
+        let user_ptr = UserPtr::<u8>::new(buffer + written * entry_size);
+        unsafe {
+            if let Err(err) = copy_to_user(user_ptr, chunk.as_ptr() as *const u8, n * entry_size) {
+                log_error!("sys_perfmon_read: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+        written += n;
+    }
+
+    let actual_ptr = UserPtr::<u8>::new(actual);
+    unsafe {
+        if let Err(err) = copy_to_user(
+            actual_ptr,
+            &written as *const usize as *const u8,
+            core::mem::size_of::<usize>(),
+        ) {
+            log_error!("sys_perfmon_read: copy_to_user actual failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: Itrace Control
+/// ============================================================================
+
+/// Control hardware instruction trace
+///
+/// # Arguments
+///
+/// * `handle` - Resource handle (must be root resource)
+/// * `action` - Action to perform
+/// * `buffer` - User pointer to an `ItraceConfig` for `ALLOC`
+/// * `buffer_size` - Size of the buffer
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_itrace_control_impl(handle: u32, action: u32, buffer: usize, buffer_size: usize) -> SyscallRet {
+    log_debug!(
+        "sys_itrace_control: handle={:#x} action={} buffer={:#x} size={}",
+        handle,
+        action,
+        buffer,
+        buffer_size
+    );
+
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_itrace_control: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let result = match action {
+        itrace_action::ALLOC => {
+            let size = core::mem::size_of::<ItraceConfig>();
+            if buffer_size != size {
+                return err_to_ret(RX_ERR_INVALID_ARGS);
+            }
+            let mut config = ItraceConfig::default();
+            unsafe {
+                if let Err(err) = copy_from_user(&mut config as *mut _ as *mut u8, UserPtr::<u8>::new(buffer), size) {
+                    log_error!("sys_itrace_control: copy_from_user failed: {:?}", err);
+                    return err_to_ret(err.into());
+                }
+            }
+            itrace::alloc(&config)
+        }
+
+        itrace_action::FREE => itrace::free(),
+
+        itrace_action::START => itrace::start(),
+
+        itrace_action::STOP => {
+            itrace::stop();
+            Ok(())
+        }
+
+        _ => {
+            log_error!("sys_itrace_control: invalid action {}", action);
+            Err(RX_ERR_INVALID_ARGS)
+        }
+    };
+
+    match result {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: Itrace Get Buffer
+/// ============================================================================
+
+/// Hand over an instruction trace buffer
+///
+/// The session must be stopped. The buffer stays valid after the session
+/// is freed, until its handle is closed.
+///
+/// # Arguments
+///
+/// * `handle` - Resource handle (must be root resource)
+/// * `index` - Buffer: the CPU in CPU mode, 0 in thread mode
+/// * `info` - User pointer to store the `ItraceBufferInfo`
+/// * `vmo_out` - User pointer to store a handle to the buffer's VMO
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_itrace_get_buffer_impl(handle: u32, index: u32, info: usize, vmo_out: usize) -> SyscallRet {
+    log_debug!("sys_itrace_get_buffer: handle={:#x} index={}", handle, index);
+
+    if let Err(err) = validate_resource(handle, ResourceKind::Root) {
+        log_error!("sys_itrace_get_buffer: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let (buffer_info, vmo) = match itrace::buffer(index as usize) {
+        Ok(buffer) => buffer,
+        Err(err) => return err_to_ret(err),
+    };
+
+    unsafe {
+        if let Err(err) = copy_to_user(
+            UserPtr::<u8>::new(info),
+            &buffer_info as *const _ as *const u8,
+            core::mem::size_of_val(&buffer_info),
+        ) {
+            log_error!("sys_itrace_get_buffer: copy_to_user info failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    let vmo_handle = match register_shared_vmo(vmo) {
+        Ok(handle) => handle,
+        Err(err) => return err_to_ret(err),
+    };
+    unsafe {
+        if let Err(err) = copy_to_user(
+            UserPtr::<u8>::new(vmo_out),
+            &vmo_handle as *const u32 as *const u8,
+            core::mem::size_of::<u32>(),
+        ) {
+            log_error!("sys_itrace_get_buffer: copy_to_user vmo failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
 
     ok_to_ret(0)
 }
@@ -548,6 +1045,26 @@ mod tests {
         assert_eq!(ktrace_action::RESET, 3);
     }
 
+    #[test]
+    fn test_profiler_chunk_holds_whole_samples() {
+        assert_eq!(PROFILER_CHUNK_SIZE % profiler::ProfilerSample::SIZE, 0);
+    }
+
+    #[test]
+    fn test_perfmon_action_consts() {
+        assert_eq!(perfmon_action::GET_PROPERTIES, 0);
+        assert_eq!(perfmon_action::START, 1);
+        assert_eq!(perfmon_action::STOP, 2);
+    }
+
+    #[test]
+    fn test_itrace_action_consts() {
+        assert_eq!(itrace_action::ALLOC, 0);
+        assert_eq!(itrace_action::FREE, 1);
+        assert_eq!(itrace_action::START, 2);
+        assert_eq!(itrace_action::STOP, 3);
+    }
+
     #[test]
     fn test_mtrace_kind_consts() {
         assert_eq!(mtrace_kind::HARDWARE, 0);
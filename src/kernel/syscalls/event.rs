@@ -14,11 +14,13 @@
 //! - `rx_event_create` - Create an event
 //! - `rx_eventpair_create` - Create an event pair
 //! - `rx_object_signal` - Signal an object
+//! - `rx_object_signal_peer` - Signal the other side of an event pair
 //!
 //! # Design
 //!
 //! - Events are binary (signaled/not signaled)
-//! - EventPairs are pairs that signal each other
+//! - EventPairs are pairs that signal each other through user signal bits
+//! - Closing the last handle to one side asserts `PEER_CLOSED` on the other
 //! - Both auto-reset and manual-reset modes supported
 //! - Multiple threads can wait on same event
 
@@ -26,6 +28,7 @@
 use crate::kernel::object::event::{self, Event, EventPair, EventFlags};
 use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
 use crate::kernel::sync::Mutex;
+use crate::kernel::syscalls::object_wait::{self, signal};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
@@ -137,6 +140,17 @@ impl EventRegistry {
 /// Global event registry
 static EVENT_REGISTRY: Mutex<EventRegistry> = Mutex::new(EventRegistry::new());
 
+/// Register an event the kernel signals and return its handle value
+///
+/// Used for system events, such as memory pressure, that userspace waits
+/// on; the kernel keeps its own reference to signal them.
+pub fn register_kernel_event(event: Arc<Event>) -> Result<u32> {
+    let event_id = EVENT_REGISTRY.lock().insert(event)?;
+
+    // TODO: Add handle to current process's handle table
+    Ok(event_id as u32)
+}
+
 /// ============================================================================
 /// EventPair Registry
 /// ============================================================================
@@ -239,13 +253,10 @@ static EVENTPAIR_REGISTRY: Mutex<EventPairRegistry> = Mutex::new(EventPairRegist
 
 /// Get the current process's handle table
 ///
-/// This is a placeholder that returns NULL for now.
-/// In a real implementation, this would use thread-local storage
-/// or per-CPU data to get the current process.
+/// Uses the same table as the handle syscalls until processes are
+/// tracked per thread.
 fn current_process_handle_table() -> Option<&'static HandleTable> {
-    // TODO: Implement proper current process tracking
-    // For now, return None to indicate not implemented
-    None
+    Some(crate::kernel::thread::current_thread_handle_table())
 }
 
 /// Look up an event from a handle value
@@ -265,7 +276,7 @@ fn lookup_event_from_handle(
 
     // Get the handle from the table
     let handle = handle_table.get(handle_val)
-        .ok_or(RX_ERR_INVALID_ARGS)?;
+        .ok_or(RX_ERR_BAD_HANDLE)?;
 
     // Validate object type (Event or EventPair both use Event base)
     let obj_type = handle.obj_type();
@@ -286,35 +297,12 @@ fn lookup_event_from_handle(
     Ok((event, handle))
 }
 
-/// Look up an eventpair from a handle value
-fn lookup_eventpair_from_handle(
-    handle_val: u32,
-    required_rights: Rights,
-) -> Result<(Arc<EventPair>, Handle)> {
-    // Get current process handle table
-    let handle_table = current_process_handle_table()
-        .ok_or(RX_ERR_NOT_SUPPORTED)?;
-
-    // Get the handle from the table
-    let handle = handle_table.get(handle_val)
-        .ok_or(RX_ERR_INVALID_ARGS)?;
-
-    // Validate object type
-    if handle.obj_type() != ObjectType::EventPair {
-        return Err(RX_ERR_WRONG_TYPE);
-    }
-
-    // Validate rights
-    handle.require(required_rights)?;
-
-    // Get eventpair ID from handle
-    let eventpair_id = handle.id as event::EventPairId;
-
-    // Get eventpair from registry
-    let eventpair = EVENTPAIR_REGISTRY.lock().get(eventpair_id)
-        .ok_or(RX_ERR_NOT_FOUND)?;
-
-    Ok((eventpair, handle))
+/// Look up an eventpair by handle value
+///
+/// Eventpair handle values are registry IDs until eventpairs are installed
+/// in the process's handle table, so there are no handle rights to check.
+fn lookup_eventpair(handle_val: u32) -> Option<Arc<EventPair>> {
+    EVENTPAIR_REGISTRY.lock().get(handle_val as event::EventPairId)
 }
 
 /// ============================================================================
@@ -323,12 +311,12 @@ fn lookup_eventpair_from_handle(
 
 /// Create a kernel object base for an event
 fn event_to_kernel_base(event: &Arc<Event>) -> KernelObjectBase {
-    KernelObjectBase::new(ObjectType::Event)
+    KernelObjectBase::with_koid(ObjectType::Event, event.koid)
 }
 
 /// Create a kernel object base for an eventpair
-fn eventpair_to_kernel_base(_eventpair: &Arc<EventPair>) -> KernelObjectBase {
-    KernelObjectBase::new(ObjectType::EventPair)
+fn eventpair_to_kernel_base(eventpair: &Arc<EventPair>) -> KernelObjectBase {
+    KernelObjectBase::with_koid(ObjectType::EventPair, eventpair.koid)
 }
 
 /// ============================================================================
@@ -443,8 +431,8 @@ pub fn sys_eventpair_create_impl() -> SyscallRet {
     let base_a = eventpair_to_kernel_base(&pair_a_arc);
     let base_b = eventpair_to_kernel_base(&pair_b_arc);
 
-    // Create handles with default rights (SIGNAL | WAIT)
-    let rights = Rights::SIGNAL | Rights::WAIT;
+    // Create handles with default rights (SIGNAL | SIGNAL_PEER | WAIT)
+    let rights = Rights::default_for_type(ObjectType::EventPair);
     let handle_a = Handle::new(&base_a as *const KernelObjectBase, rights);
     let handle_b = Handle::new(&base_b as *const KernelObjectBase, rights);
 
@@ -471,48 +459,188 @@ pub fn sys_eventpair_create_impl() -> SyscallRet {
 /// # Arguments
 ///
 /// * `handle_val` - Handle value of the object to signal
-/// * `options` - Signal options
-///   - bit 0: user facing signal mask (currently unused)
+/// * `clear_mask` - Signal bits to clear
+/// * `set_mask` - Signal bits to set, after clearing
+///
+/// Events are binary: any set bit signals them and any cleared bit
+/// without a set bit unsignals them. Eventpairs keep the bits themselves
+/// and only accept user signals.
 ///
 /// # Returns
 ///
 /// * On success: 0
 /// * On error: Negative error code
-pub fn sys_object_signal_impl(handle_val: u32, options: u32) -> SyscallRet {
-    log_debug!("sys_object_signal: handle={} options={:#x}", handle_val, options);
-
-    // Extract signal mask from options
-    let signal_mask = match options {
-        0 => crate::kernel::syscalls::object_wait::signal::USER_0,
-        _ => options as u64,
-    };
+pub fn sys_object_signal_impl(handle_val: u32, clear_mask: u64, set_mask: u64) -> SyscallRet {
+    log_debug!(
+        "sys_object_signal: handle={} clear={:#x} set={:#x}",
+        handle_val, clear_mask, set_mask
+    );
 
     // Try to look up as an event first
     if let Ok((event, _handle)) = lookup_event_from_handle(handle_val, Rights::SIGNAL) {
-        event.signal();
+        if set_mask != 0 {
+            event.signal();
+        } else if clear_mask != 0 {
+            event.unsignal();
+        }
 
         // Wake up any threads waiting on this object
-        let woken = crate::kernel::syscalls::object_wait::wake_waiters(handle_val, signal_mask);
+        let woken = object_wait::wake_waiters(handle_val, set_mask);
         log_debug!("sys_object_signal: signaled event, woke {} waiters", woken);
 
         return ok_to_ret(0);
     }
 
     // Try to look up as an eventpair
-    if let Ok((eventpair, _handle)) = lookup_eventpair_from_handle(handle_val, Rights::SIGNAL) {
-        eventpair.signal();
-
-        // Wake up any threads waiting on this object
-        let woken = crate::kernel::syscalls::object_wait::wake_waiters(handle_val, signal_mask);
-        log_debug!("sys_object_signal: signaled eventpair, woke {} waiters", woken);
-
-        return ok_to_ret(0);
+    if let Some(eventpair) = lookup_eventpair(handle_val) {
+        return match signal_eventpair(&eventpair, eventpair.id, clear_mask, set_mask) {
+            Ok(()) => ok_to_ret(0),
+            Err(err) => err_to_ret(err),
+        };
     }
 
     // TODO: Support signaling other object types (channel, timer, etc.)
 
     log_error!("sys_object_signal: handle not found or not signalable");
-    err_to_ret(RX_ERR_INVALID_ARGS)
+    err_to_ret(RX_ERR_BAD_HANDLE)
+}
+
+/// ============================================================================
+/// Syscall: Object Signal Peer
+/// ============================================================================
+
+/// Signal the peer of an eventpair syscall handler
+///
+/// # Arguments
+///
+/// * `handle_val` - Handle value of one side of the pair
+/// * `clear_mask` - User signal bits to clear on the other side
+/// * `set_mask` - User signal bits to set on the other side, after clearing
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+///   - `RX_ERR_BAD_HANDLE` if the handle is not an eventpair
+///   - `RX_ERR_INVALID_ARGS` if a mask has non-user bits
+///   - `RX_ERR_PEER_CLOSED` if the other side has no handles left
+pub fn sys_object_signal_peer_impl(handle_val: u32, clear_mask: u64, set_mask: u64) -> SyscallRet {
+    log_debug!(
+        "sys_object_signal_peer: handle={} clear={:#x} set={:#x}",
+        handle_val, clear_mask, set_mask
+    );
+
+    let eventpair = match lookup_eventpair(handle_val) {
+        Some(eventpair) => eventpair,
+        None => {
+            log_error!("sys_object_signal_peer: handle not found or has no peer");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+    };
+
+    if eventpair.is_peer_closed() {
+        return err_to_ret(RX_ERR_PEER_CLOSED);
+    }
+
+    let peer_id = eventpair.peer_id();
+    let peer = match EVENTPAIR_REGISTRY.lock().get(peer_id) {
+        Some(peer) => peer,
+        None => return err_to_ret(RX_ERR_PEER_CLOSED),
+    };
+
+    match signal_eventpair(&peer, peer_id, clear_mask, set_mask) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// Update one side of an eventpair and wake its waiters
+///
+/// `handle_val` is the handle value waiters on that side used.
+fn signal_eventpair(
+    eventpair: &EventPair,
+    handle_val: event::EventPairId,
+    clear_mask: u64,
+    set_mask: u64,
+) -> Result {
+    if (clear_mask | set_mask) & !signal::USER_ALL != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    let signals = eventpair.update_signals(clear_mask, set_mask);
+
+    if set_mask != 0 {
+        let woken = object_wait::wake_waiters(handle_val as u32, signals);
+        log_debug!("signal_eventpair: id={} signals={:#x}, woke {} waiters",
+            eventpair.id, signals, woken);
+    }
+
+    Ok(())
+}
+
+/// Get the signals asserted on an event
+///
+/// A signaled event asserts `USER_0`. Returns `None` if `handle_val` is
+/// not an event.
+pub fn event_signals(handle_val: u32) -> Option<u64> {
+    let event = EVENT_REGISTRY.lock().get(handle_val as event::EventId)?;
+    Some(if event.is_signaled() { signal::USER_0 } else { 0 })
+}
+
+/// Get the signals asserted on an eventpair
+///
+/// Returns `None` if `handle_val` is not an eventpair.
+pub fn eventpair_signals(handle_val: u32) -> Option<u64> {
+    let eventpair = lookup_eventpair(handle_val)?;
+    let mut signals = eventpair.signals();
+    if eventpair.is_peer_closed() {
+        signals |= signal::PEER_CLOSED;
+    }
+    Some(signals)
+}
+
+/// Get the koids of an eventpair side and of its other side
+///
+/// Returns `None` if `handle_val` is not an eventpair.
+pub fn eventpair_koids(handle_val: u32) -> Option<(u64, u64)> {
+    lookup_eventpair(handle_val).map(|eventpair| (eventpair.koid, eventpair.peer_koid))
+}
+
+/// ============================================================================
+/// Eventpair Close
+/// ============================================================================
+
+/// Drop a handle to an eventpair
+///
+/// On the last handle the side is removed from the registry and the other
+/// side is marked peer closed, waking its waiters with `PEER_CLOSED`.
+///
+/// Returns false if `handle_val` is not an eventpair.
+pub fn close_eventpair_handle(handle_val: u32) -> bool {
+    let eventpair = match lookup_eventpair(handle_val) {
+        Some(eventpair) => eventpair,
+        None => return false,
+    };
+
+    if !eventpair.ref_dec() {
+        return true;
+    }
+
+    EVENTPAIR_REGISTRY.lock().remove(eventpair.id);
+
+    let peer_id = eventpair.peer_id();
+    if let Some(peer) = EVENTPAIR_REGISTRY.lock().get(peer_id) {
+        if peer.set_peer_closed() {
+            let woken = object_wait::wake_waiters(
+                peer_id as u32,
+                peer.signals() | signal::PEER_CLOSED,
+            );
+            log_debug!("close_eventpair_handle: id={} closed, woke {} peer waiters",
+                eventpair.id, woken);
+        }
+    }
+
+    true
 }
 
 /// ============================================================================
@@ -632,4 +760,76 @@ mod tests {
         assert_eq!(pair_a.peer.load(Ordering::Relaxed), pair_b.id as usize);
         assert_eq!(pair_b.peer.load(Ordering::Relaxed), pair_a.id as usize);
     }
+
+    /// Register both sides of a new eventpair and return their handle values
+    fn create_registered_pair() -> (u32, u32) {
+        let (pair_a, pair_b) = EventPair::create().unwrap();
+        let id_a = EVENTPAIR_REGISTRY.lock().insert(Arc::new(pair_a)).unwrap();
+        let id_b = EVENTPAIR_REGISTRY.lock().insert(Arc::new(pair_b)).unwrap();
+        (id_a as u32, id_b as u32)
+    }
+
+    #[test]
+    fn test_eventpair_signal_self() {
+        let (a, b) = create_registered_pair();
+
+        assert_eq!(sys_object_signal_impl(a, 0, signal::USER_1), 0);
+        assert_eq!(eventpair_signals(a), Some(signal::USER_1));
+        assert_eq!(eventpair_signals(b), Some(0));
+
+        assert_eq!(sys_object_signal_impl(a, signal::USER_1, 0), 0);
+        assert_eq!(eventpair_signals(a), Some(0));
+    }
+
+    #[test]
+    fn test_eventpair_signal_peer() {
+        let (a, b) = create_registered_pair();
+
+        assert_eq!(sys_object_signal_peer_impl(a, 0, signal::USER_0 | signal::USER_2), 0);
+        assert_eq!(eventpair_signals(a), Some(0));
+        assert_eq!(eventpair_signals(b), Some(signal::USER_0 | signal::USER_2));
+
+        assert_eq!(sys_object_signal_peer_impl(b, 0, signal::USER_3), 0);
+        assert_eq!(eventpair_signals(a), Some(signal::USER_3));
+    }
+
+    #[test]
+    fn test_eventpair_signal_rejects_non_user_bits() {
+        let (a, _b) = create_registered_pair();
+
+        assert_eq!(sys_object_signal_impl(a, 0, signal::PEER_CLOSED), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_object_signal_peer_impl(a, signal::HANDLE_CLOSED, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_eventpair_signal_peer_bad_handle() {
+        assert_eq!(sys_object_signal_peer_impl(0xdead_beef, 0, signal::USER_0), err_to_ret(RX_ERR_BAD_HANDLE));
+    }
+
+    #[test]
+    fn test_eventpair_peer_closed() {
+        let (a, b) = create_registered_pair();
+
+        assert!(close_eventpair_handle(a));
+        assert!(lookup_eventpair(a).is_none());
+        assert_eq!(eventpair_signals(b), Some(signal::PEER_CLOSED));
+        assert_eq!(sys_object_signal_peer_impl(b, 0, signal::USER_0), err_to_ret(RX_ERR_PEER_CLOSED));
+
+        // The surviving side can still signal itself
+        assert_eq!(sys_object_signal_impl(b, 0, signal::USER_0), 0);
+        assert_eq!(eventpair_signals(b), Some(signal::USER_0 | signal::PEER_CLOSED));
+    }
+
+    #[test]
+    fn test_eventpair_close_waits_for_last_handle() {
+        let (a, b) = create_registered_pair();
+        lookup_eventpair(a).unwrap().ref_inc();
+
+        assert!(close_eventpair_handle(a));
+        assert_eq!(eventpair_signals(b), Some(0));
+
+        assert!(close_eventpair_handle(a));
+        assert_eq!(eventpair_signals(b), Some(signal::PEER_CLOSED));
+        assert!(!close_eventpair_handle(a));
+    }
 }
@@ -18,6 +18,7 @@
 //! # Design
 //!
 //! - FIFO pairs are created together (like eventpairs)
+//! - Writes land in the peer's queue; reads drain the caller's own
 //! - Fixed element size
 //! - Fixed capacity
 //! - Non-blocking reads/writes
@@ -101,7 +102,9 @@ impl FifoEntry {
         (self.capacity * self.elem_size - data.len()) / self.elem_size
     }
 
-    /// Write data to FIFO
+    /// Queue data for this end to read
+    ///
+    /// Only whole elements are queued.
     pub fn write(&self, bytes: &[u8]) -> Result<usize> {
         if self.closed.load(Ordering::Relaxed) {
             return Err(RX_ERR_PEER_CLOSED);
@@ -109,12 +112,12 @@ impl FifoEntry {
 
         let mut data = self.data.lock();
         let avail = (self.capacity * self.elem_size) - data.len();
+        let to_write = bytes.len().min(avail) / self.elem_size * self.elem_size;
 
-        if avail == 0 {
+        if to_write == 0 {
             return Err(RX_ERR_SHOULD_WAIT);
         }
 
-        let to_write = bytes.len().min(avail);
         for &b in bytes.iter().take(to_write) {
             data.push_back(b);
         }
@@ -383,6 +386,22 @@ pub fn sys_fifo_write_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
+    if count == 0 {
+        log_error!("sys_fifo_write: zero count");
+        return err_to_ret(RX_ERR_OUT_OF_RANGE);
+    }
+
+    // Elements are delivered to the peer's queue
+    let peer = unsafe { FIFO_REGISTRY.get(fifo.peer_id.load(Ordering::Relaxed)) };
+    let peer = match peer {
+        Some(p) => p,
+        None => return err_to_ret(RX_ERR_PEER_CLOSED),
+    };
+
+    // No more than a full queue can be written, so don't size the buffer
+    // (or overflow) on a larger count
+    let count = count.min(peer.capacity);
+
     // Calculate total bytes to write
     let total_bytes = count * elem_size;
 
@@ -399,7 +418,7 @@ pub fn sys_fifo_write_impl(
     }
 
     // Write to FIFO
-    let actual_written = match fifo.write(&buf) {
+    let actual_written = match peer.write(&buf) {
         Ok(n) => n / elem_size,
         Err(err) => {
             // Map SHOULD_WAIT to a specific error
@@ -481,6 +500,14 @@ pub fn sys_fifo_read_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
+    if count == 0 {
+        log_error!("sys_fifo_read: zero count");
+        return err_to_ret(RX_ERR_OUT_OF_RANGE);
+    }
+
+    // No more than a full queue can be read
+    let count = count.min(fifo.capacity);
+
     // Calculate total bytes to read
     let total_bytes = count * elem_size;
 
@@ -607,4 +634,28 @@ mod tests {
         let result = sys_fifo_read_impl(0, 8, 0, 1, 0);
         assert!(result < 0);
     }
+
+    #[test]
+    fn test_fifo_zero_count() {
+        let mut handles = [0u64; 2];
+        let result = sys_fifo_create_impl(
+            16, 8, 0,
+            &mut handles[0] as *mut u64 as usize,
+            &mut handles[1] as *mut u64 as usize,
+        );
+        assert!(result >= 0);
+
+        let data = [0u8; 8];
+        let result = sys_fifo_write_impl(handles[0] as u32, 8, data.as_ptr() as usize, 0, 0);
+        assert_eq!(result, err_to_ret(RX_ERR_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn test_fifo_entry_whole_elements() {
+        let fifo = FifoEntry::new(1, 4, 2);
+        assert_eq!(fifo.write(&[0u8; 6]), Ok(4));
+        assert_eq!(fifo.write(&[0u8; 8]), Ok(4));
+        assert_eq!(fifo.write(&[0u8; 4]), Err(RX_ERR_SHOULD_WAIT));
+        assert_eq!(fifo.available_read(), 2);
+    }
 }
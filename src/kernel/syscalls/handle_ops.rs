@@ -17,8 +17,9 @@
 
 
 use crate::kernel::object::{Handle, HandleOwner, HandleTable, Rights};
+use crate::kernel::object::handle::MAX_HANDLES;
 use crate::kernel::usercopy::{copy_from_user, UserPtr};
-use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::{counter, ddk, event, object_wait, SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 
@@ -59,21 +60,24 @@ pub fn sys_handle_close_impl(handle_value: u32) -> SyscallRet {
     // Get the current process's handle table
     let handle_table = crate::kernel::thread::current_thread_handle_table();
 
-    // Remove the handle
-    match handle_table.remove(handle_value) {
-        Ok(_closed) => {
-            log_debug!("sys_handle_close: success");
-            ok_to_ret(0)
-        }
-        Err(_) => {
-            log_error!("sys_handle_close: bad handle");
-            err_to_ret(RX_ERR_BAD_HANDLE)
+    if !is_open(handle_table, handle_value) {
+        // Closing an empty slot again is not an error
+        if (handle_value as usize) < MAX_HANDLES {
+            return ok_to_ret(0);
         }
+        log_error!("sys_handle_close: bad handle");
+        return err_to_ret(RX_ERR_BAD_HANDLE);
     }
+
+    close_one(handle_table, handle_value);
+    log_debug!("sys_handle_close: success");
+    ok_to_ret(0)
 }
 
 /// Close multiple handles
 ///
+/// Either every handle is closed or none is; see [`close_handles`].
+///
 /// # Arguments
 ///
 /// * `handles` - User pointer to array of handles
@@ -83,6 +87,8 @@ pub fn sys_handle_close_impl(handle_value: u32) -> SyscallRet {
 ///
 /// * On success: 0
 /// * On error: Negative error code
+///   - `RX_ERR_INVALID_ARGS` if `num_handles` exceeds `MAX_CLOSE_MANY`
+///   - `RX_ERR_BAD_HANDLE` if any entry is not an open handle or is repeated
 pub fn sys_handle_close_many_impl(handles: usize, num_handles: usize) -> SyscallRet {
     log_debug!(
         "sys_handle_close_many: handles={:#x} num_handles={}",
@@ -94,7 +100,7 @@ pub fn sys_handle_close_many_impl(handles: usize, num_handles: usize) -> Syscall
     }
 
     // Limit the number of handles to prevent abuse
-    if num_handles > 1024 {
+    if num_handles > MAX_CLOSE_MANY {
         log_error!("sys_handle_close_many: too many handles");
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
@@ -112,24 +118,95 @@ pub fn sys_handle_close_many_impl(handles: usize, num_handles: usize) -> Syscall
     // Get the current process's handle table
     let handle_table = crate::kernel::thread::current_thread_handle_table();
 
-    // Remove each handle
-    let mut removed = 0;
-    for &handle_value in &handle_buf {
+    match close_handles(handle_table, &handle_buf) {
+        Ok(closed) => {
+            log_debug!("sys_handle_close_many: closed {} handles", closed);
+            ok_to_ret(0)
+        }
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Bulk Close
+/// ============================================================================
+
+/// Maximum number of handles `rx_handle_close_many` accepts
+pub const MAX_CLOSE_MANY: usize = 1024;
+
+/// Check whether `handle_value` names an open handle
+///
+/// Eventpair and counter handle values are registry IDs, not table slots.
+fn is_open(handle_table: &HandleTable, handle_value: u32) -> bool {
+    handle_table.get(handle_value).is_some()
+        || event::eventpair_signals(handle_value).is_some()
+        || counter::counter_signals(handle_value).is_some()
+        || ddk::is_pmt_handle(handle_value)
+}
+
+/// Close one handle that [`is_open`] accepted
+fn close_one(handle_table: &HandleTable, handle_value: u32) {
+    let closed = if handle_table.get(handle_value).is_some() {
+        handle_table.remove(handle_value).is_ok()
+    } else {
+        event::close_eventpair_handle(handle_value)
+            || counter::close_counter_handle(handle_value)
+            || ddk::close_pmt_handle(handle_value)
+    };
+
+    // Waits on the handle fail with RX_ERR_CANCELED
+    if closed {
+        object_wait::cancel_waiters(handle_value);
+    }
+}
+
+/// Close every handle in `handles`, or none of them
+///
+/// `ZX_HANDLE_INVALID` entries are skipped. Every other entry must name a
+/// distinct open handle; otherwise nothing is closed and the call fails
+/// with `RX_ERR_BAD_HANDLE`.
+///
+/// # Returns
+///
+/// Number of handles closed
+pub fn close_handles(handle_table: &HandleTable, handles: &[u32]) -> Result<usize> {
+    let mut seen = alloc::collections::BTreeSet::new();
+    for &handle_value in handles {
         if handle_value == ZX_HANDLE_INVALID {
             continue;
         }
-
-        match handle_table.remove(handle_value) {
-            Ok(_) => removed += 1,
-            Err(_) => {
-                // Continue removing other handles even if one fails
-                log_debug!("sys_handle_close_many: bad handle {:#x}", handle_value);
-            }
+        // A repeated handle would already be closed by the time we reach it
+        if !seen.insert(handle_value) || !is_open(handle_table, handle_value) {
+            log_debug!("close_handles: bad handle {:#x}", handle_value);
+            return Err(RX_ERR_BAD_HANDLE);
         }
     }
 
-    log_debug!("sys_handle_close_many: removed {} handles", removed);
-    ok_to_ret(0)
+    Ok(close_each(handle_table, seen.into_iter()))
+}
+
+/// Close handles already known to be open and distinct
+fn close_each(handle_table: &HandleTable, handles: impl Iterator<Item = u32>) -> usize {
+    let mut closed = 0;
+    for handle_value in handles {
+        close_one(handle_table, handle_value);
+        closed += 1;
+    }
+    closed
+}
+
+/// Close every handle in a process's handle table
+///
+/// Used by process teardown. Every slot is closed, including slot 0,
+/// which `rx_handle_close_many` treats as `ZX_HANDLE_INVALID`.
+/// Registry-backed objects (eventpairs, counters) are not tracked per
+/// process, so their handles are not found here.
+///
+/// # Returns
+///
+/// Number of handles closed
+pub fn close_all_handles(handle_table: &HandleTable) -> usize {
+    close_each(handle_table, handle_table.handle_values().into_iter())
 }
 
 /// Helper function for handle duplicate and replace
@@ -426,6 +503,37 @@ mod tests {
         assert!(result >= 0);
     }
 
+    #[test]
+    fn test_close_handles_all_or_nothing() {
+        let table = HandleTable::new();
+        let open = crate::kernel::syscalls::counter::sys_counter_create_impl(0) as u32;
+
+        assert_eq!(close_handles(&table, &[open, 0xdead_beef]), Err(RX_ERR_BAD_HANDLE));
+        assert!(counter::counter_signals(open).is_some());
+
+        assert_eq!(close_handles(&table, &[open, open]), Err(RX_ERR_BAD_HANDLE));
+        assert!(counter::counter_signals(open).is_some());
+
+        assert_eq!(close_handles(&table, &[ZX_HANDLE_INVALID, open]), Ok(1));
+        assert!(counter::counter_signals(open).is_none());
+    }
+
+    #[test]
+    fn test_close_handles_empty() {
+        let table = HandleTable::new();
+        assert_eq!(close_handles(&table, &[]), Ok(0));
+        assert_eq!(close_handles(&table, &[ZX_HANDLE_INVALID]), Ok(0));
+        assert_eq!(close_all_handles(&table), 0);
+    }
+
+    #[test]
+    fn test_handle_close_many_too_many() {
+        assert_eq!(
+            sys_handle_close_many_impl(0x1000, MAX_CLOSE_MANY + 1),
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        );
+    }
+
     #[test]
     fn test_handle_same_rights_const() {
         assert_eq!(ZX_RIGHT_SAME_RIGHTS, 0x80000000);
@@ -10,136 +10,89 @@
 //!
 //! # Syscalls Implemented
 //!
-//! - `rx_guest_create` - Create a guest VM
-//! - `rx_guest_set_trap` - Set a trap on a memory region
+//! - `rx_hypervisor_create` - Create a guest VM
+//! - `rx_hypervisor_op` - Map or unmap guest memory
+//! - `rx_guest_set_trap` - Set a trap on a memory or I/O port range
 //! - `rx_vcpu_create` - Create a virtual CPU
-//! - `rx_vcpu_resume` - Resume VCPU execution
+//! - `rx_vcpu_enter` - Run a VCPU until it exits to the VMM
+//! - `rx_vcpu_kick` - Make a running VCPU exit to the VMM
 //! - `rx_vcpu_interrupt` - Send interrupt to VCPU
 //! - `rx_vcpu_read_state` - Read VCPU state
 //! - `rx_vcpu_write_state` - Write VCPU state
 //!
 //! # Design
 //!
-//! - Guest VM management
-//! - VCPU creation and control
-//! - Memory trapping
-//! - State save/restore
+//! A VMM creates a guest with the hypervisor resource, backs its memory
+//! with VMOs through `rx_hypervisor_op`, sets traps on the device ranges
+//! it emulates, then runs one thread per VCPU in a loop around
+//! `rx_vcpu_enter`. Each return carries a port packet describing the exit
+//! (see [`crate::kernel::hypervisor`]).
+//!
+//! Guest and VCPU handle values are registry IDs, like counters, until
+//! they are installed in the process's handle table.
 
 
-use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
+use crate::kernel::hypervisor::{Guest, GuestId, Trap, Vcpu, VcpuId, VcpuState, MAX_GUESTS, MAX_VCPUS};
+use crate::kernel::object::Rights;
+use crate::kernel::sync::Mutex;
+use crate::kernel::syscalls::{port, resource, vmo};
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
-use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+pub use crate::kernel::hypervisor::{guest_map_flags, trap_kind, vcpu_state};
 
 // Import logging macros
 use crate::{log_debug, log_error, log_info};
 
 /// ============================================================================
-/// Trap Types
+/// Guest Operations
 /// ============================================================================
 
-/// Trap kinds
-pub mod trap_kind {
-    /// Memory-mapped I/O trap
-    pub const MMIO: u32 = 1;
+/// `rx_hypervisor_op` operations
+///
+/// The low byte of `op` selects the operation; for `MAP`, the bits from
+/// `FLAGS_SHIFT` up are the `guest_map_flags`.
+pub mod guest_op {
+    /// Back a guest-physical range with a VMO range
+    pub const MAP: u32 = 1;
+
+    /// Remove guest-physical mappings
+    pub const UNMAP: u32 = 2;
 
-    /// Input/output port trap
-    pub const IO: u32 = 2;
+    /// Mask of the operation
+    pub const OP_MASK: u32 = 0xFF;
 
-    /// Write protection trap
-    pub const WRITE: u32 = 3;
+    /// Shift of the map flags
+    pub const FLAGS_SHIFT: u32 = 8;
 }
 
 /// ============================================================================
-/// VCPU State Types
+/// Guest and VCPU Registries
 /// ============================================================================
 
-/// VCPU state kinds
-pub mod vcpu_state {
-    /// x86 registers
-    pub const X86_REGS: u32 = 1;
+/// Global guest registry
+static GUEST_REGISTRY: Mutex<BTreeMap<GuestId, Arc<Guest>>> = Mutex::new(BTreeMap::new());
 
-    /// x86 FPU/SIMD registers
-    pub const X86_FPREGS: u32 = 2;
+/// Global VCPU registry
+static VCPU_REGISTRY: Mutex<BTreeMap<VcpuId, Arc<Vcpu>>> = Mutex::new(BTreeMap::new());
 
-    /// ARM registers
-    pub const ARM_REGS: u32 = 1;
-
-    /// ARM FPU/SIMD registers
-    pub const ARM_FPREGS: u32 = 2;
+/// Look up a guest by handle value
+fn lookup_guest(handle: u32) -> Result<Arc<Guest>> {
+    GUEST_REGISTRY.lock().get(&(handle as GuestId)).cloned().ok_or(RX_ERR_BAD_HANDLE)
 }
 
-/// ============================================================================
-/// VCPU State Structure
-/// ============================================================================
-
-/// VCPU state (simplified representation)
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct VcpuState {
-    /// Architecture-specific state
-    pub regs: [u64; 64],
+/// Look up a VCPU by handle value
+fn lookup_vcpu(handle: u32) -> Result<Arc<Vcpu>> {
+    VCPU_REGISTRY.lock().get(&(handle as VcpuId)).cloned().ok_or(RX_ERR_BAD_HANDLE)
 }
 
-/// ============================================================================
-/// Resource Validation
-/// ============================================================================
-
-/// Resource kind for hypervisor
-const ZX_RSRC_KIND_HYPERVISOR: u32 = 4;
-
 /// Validate a hypervisor resource handle
-///
-/// # Arguments
-///
-/// * `handle` - Resource handle value
-///
-/// # Returns
-///
-/// - Ok(()) if handle is valid
-/// - Err on failure
 fn validate_hypervisor_resource(handle: u32) -> Result {
-    // TODO: Implement proper handle validation
-    // For now, only handle 0 (root resource) is valid
-    if handle != 0 {
-        return Err(RX_ERR_ACCESS_DENIED);
-    }
-
-    Ok(())
-}
-
-/// ============================================================================
-/// Guest Registry
-/// ============================================================================
-
-/// Maximum number of guests in the system
-const MAX_GUESTS: usize = 128;
-
-/// Next guest ID counter
-static mut NEXT_GUEST_ID: AtomicU64 = AtomicU64::new(1);
-
-/// Allocate a new guest ID
-fn alloc_guest_id() -> u64 {
-    unsafe { NEXT_GUEST_ID.fetch_add(1, Ordering::Relaxed) }
-}
-
-/// ============================================================================
-/// VCPU Registry
-/// ============================================================================
-
-/// Maximum number of VCPUs in the system
-const MAX_VCPUS: usize = 512;
-
-/// Next VCPU ID counter
-static mut NEXT_VCPU_ID: AtomicU64 = AtomicU64::new(1);
-
-/// Allocate a new VCPU ID
-fn alloc_vcpu_id() -> u64 {
-    unsafe { NEXT_VCPU_ID.fetch_add(1, Ordering::Relaxed) }
+    resource::validate(handle, resource::resource_kind::HYPERVISOR, 0, 0)
 }
 
 /// ============================================================================
@@ -152,19 +105,12 @@ fn alloc_vcpu_id() -> u64 {
 ///
 /// * `resource_handle` - Hypervisor resource handle
 /// * `options` - Creation options (must be 0)
-/// * `guest_handle_out` - User pointer to store guest handle
-/// * `vmar_handle_out` - User pointer to store VMAR handle
 ///
 /// # Returns
 ///
-/// * On success: 0
+/// * On success: Handle value for the new guest
 /// * On error: Negative error code
-pub fn sys_guest_create_impl(
-    resource_handle: u32,
-    options: u32,
-    guest_handle_out: usize,
-    vmar_handle_out: usize,
-) -> SyscallRet {
+pub fn sys_guest_create_impl(resource_handle: u32, options: u32) -> SyscallRet {
     log_debug!(
         "sys_guest_create: resource={:#x} options={:#x}",
         resource_handle, options
@@ -182,38 +128,79 @@ pub fn sys_guest_create_impl(
         return err_to_ret(err);
     }
 
-    // Allocate new guest ID
-    let guest_id = alloc_guest_id();
-
-    // TODO: Implement proper guest creation
-    // For now, just return the ID
-
-    // Write handles to user space
-    if guest_handle_out != 0 {
-        let user_ptr = UserPtr::<u8>::new(guest_handle_out);
-        unsafe {
-            if let Err(err) = copy_to_user(user_ptr, &guest_id as *const u64 as *const u8, 8) {
-                log_error!("sys_guest_create: copy_to_user failed: {:?}", err);
-                return err_to_ret(err.into());
-            }
+    let guest = match Guest::new() {
+        Ok(guest) => Arc::new(guest),
+        Err(err) => return err_to_ret(err),
+    };
+    let handle_value = guest.id as u32;
+
+    {
+        let mut registry = GUEST_REGISTRY.lock();
+        if registry.len() >= MAX_GUESTS {
+            log_error!("sys_guest_create: too many guests");
+            return err_to_ret(RX_ERR_NO_RESOURCES);
         }
+        registry.insert(guest.id, guest);
     }
 
-    if vmar_handle_out != 0 {
-        // For now, just use guest_id + 1 as vmar_id
-        let vmar_id = guest_id + 1;
-        let user_ptr = UserPtr::<u8>::new(vmar_handle_out);
-        unsafe {
-            if let Err(err) = copy_to_user(user_ptr, &vmar_id as *const u64 as *const u8, 8) {
-                log_error!("sys_guest_create: copy_to_user failed: {:?}", err);
-                return err_to_ret(err.into());
-            }
-        }
-    }
+    log_debug!("sys_guest_create: success handle={:#x}", handle_value);
+    ok_to_ret(handle_value as usize)
+}
 
-    log_debug!("sys_guest_create: success guest_id={}", guest_id);
+/// ============================================================================
+/// Syscall: Guest Op
+/// ============================================================================
 
-    ok_to_ret(0)
+/// Map or unmap guest memory syscall handler
+///
+/// # Arguments
+///
+/// * `handle_val` - Guest handle value
+/// * `op` - One of `guest_op`, with map flags above `FLAGS_SHIFT`
+/// * `addr` - Guest-physical address
+/// * `vmo_handle` - VMO to map (ignored by `UNMAP`)
+/// * `vmo_offset` - Offset in the VMO (ignored by `UNMAP`)
+/// * `len` - Length in bytes
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_guest_op_impl(
+    handle_val: u32,
+    op: u32,
+    addr: u64,
+    vmo_handle: u32,
+    vmo_offset: usize,
+    len: usize,
+) -> SyscallRet {
+    log_debug!(
+        "sys_guest_op: handle={:#x} op={:#x} addr={:#x} vmo={:#x} offset={:#x} len={:#x}",
+        handle_val, op, addr, vmo_handle, vmo_offset, len
+    );
+
+    let guest = match lookup_guest(handle_val) {
+        Ok(guest) => guest,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let flags = op >> guest_op::FLAGS_SHIFT;
+    let result = match op & guest_op::OP_MASK {
+        guest_op::MAP => {
+            vmo::lookup_vmo_from_handle(vmo_handle, Rights::MAP | Rights::READ | Rights::WRITE)
+                .and_then(|(vmo, _handle)| guest.map(addr, vmo, vmo_offset, len, flags))
+        }
+        guest_op::UNMAP if flags == 0 => guest.unmap(addr, len),
+        _ => Err(RX_ERR_INVALID_ARGS),
+    };
+
+    match result {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => {
+            log_debug!("sys_guest_op: failed: {:?}", err);
+            err_to_ret(err)
+        }
+    }
 }
 
 /// ============================================================================
@@ -228,7 +215,7 @@ pub fn sys_guest_create_impl(
 /// * `kind` - Trap kind
 /// * `addr` - Address to trap
 /// * `size` - Size of trapped region
-/// * `port_handle` - Port handle for notifications
+/// * `port_handle` - Port for `BELL` packets; must be 0 for other kinds
 /// * `key` - Key for port packets
 ///
 /// # Returns
@@ -248,11 +235,25 @@ pub fn sys_guest_set_trap_impl(
         handle_val, kind, addr, size, port_handle, key
     );
 
-    // TODO: Implement proper trap setup
-    // For now, just log
-    log_info!("Guest set trap: addr={:#x} size={}", addr, size);
+    let guest = match lookup_guest(handle_val) {
+        Ok(guest) => guest,
+        Err(err) => return err_to_ret(err),
+    };
 
-    ok_to_ret(0)
+    let port = if port_handle != 0 {
+        if !port::port_exists(port_handle) {
+            log_error!("sys_guest_set_trap: port not found");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+        Some(port_handle as u64)
+    } else {
+        None
+    };
+
+    match guest.set_trap(Trap { kind, addr, len: size, port, key }) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -266,18 +267,12 @@ pub fn sys_guest_set_trap_impl(
 /// * `guest_handle` - Guest handle value
 /// * `options` - Creation options (must be 0)
 /// * `entry` - Entry point address
-/// * `handle_out` - User pointer to store VCPU handle
 ///
 /// # Returns
 ///
-/// * On success: 0
+/// * On success: Handle value for the new VCPU
 /// * On error: Negative error code
-pub fn sys_vcpu_create_impl(
-    guest_handle: u32,
-    options: u32,
-    entry: u64,
-    handle_out: usize,
-) -> SyscallRet {
+pub fn sys_vcpu_create_impl(guest_handle: u32, options: u32, entry: u64) -> SyscallRet {
     log_debug!(
         "sys_vcpu_create: guest={:#x} options={:#x} entry={:#x}",
         guest_handle, options, entry
@@ -289,63 +284,97 @@ pub fn sys_vcpu_create_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // Allocate new VCPU ID
-    let vcpu_id = alloc_vcpu_id();
+    let guest = match lookup_guest(guest_handle) {
+        Ok(guest) => guest,
+        Err(err) => return err_to_ret(err),
+    };
 
-    // TODO: Implement proper VCPU creation
-    // For now, just return the ID
+    let vcpu = match Vcpu::new(guest, entry) {
+        Ok(vcpu) => Arc::new(vcpu),
+        Err(err) => {
+            log_debug!("sys_vcpu_create: failed: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+    let handle_value = vcpu.id as u32;
+    VCPU_REGISTRY.lock().insert(vcpu.id, vcpu);
 
-    // Write handle to user space
-    if handle_out != 0 {
-        let user_ptr = UserPtr::<u8>::new(handle_out);
-        unsafe {
-            if let Err(err) = copy_to_user(user_ptr, &vcpu_id as *const u64 as *const u8, 8) {
-                log_error!("sys_vcpu_create: copy_to_user failed: {:?}", err);
-                return err_to_ret(err.into());
-            }
+    log_debug!("sys_vcpu_create: success handle={:#x}", handle_value);
+    ok_to_ret(handle_value as usize)
+}
+
+/// ============================================================================
+/// Syscall: VCPU Enter
+/// ============================================================================
+
+/// Run a VCPU syscall handler
+///
+/// Blocks until the VCPU exits to the VMM.
+///
+/// # Arguments
+///
+/// * `handle_val` - VCPU handle value
+/// * `packet_out` - User pointer to store the exit packet
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_vcpu_enter_impl(handle_val: u32, packet_out: usize) -> SyscallRet {
+    log_debug!("sys_vcpu_enter: handle={:#x}", handle_val);
+
+    let vcpu = match lookup_vcpu(handle_val) {
+        Ok(vcpu) => vcpu,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let packet = match vcpu.enter() {
+        Ok(packet) => packet,
+        Err(err) => {
+            log_debug!("sys_vcpu_enter: failed: {:?}", err);
+            return err_to_ret(err);
         }
-    }
+    };
 
-    log_debug!("sys_vcpu_create: success vcpu_id={}", vcpu_id);
+    let user_ptr = UserPtr::<u8>::new(packet_out);
+    unsafe {
+        if let Err(err) = copy_to_user(
+            user_ptr,
+            &packet as *const port::PortPacket as *const u8,
+            core::mem::size_of::<port::PortPacket>(),
+        ) {
+            log_error!("sys_vcpu_enter: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
 
     ok_to_ret(0)
 }
 
 /// ============================================================================
-/// Syscall: VCPU Resume
+/// Syscall: VCPU Kick
 /// ============================================================================
 
-/// Resume VCPU execution syscall handler
+/// Make a VCPU exit to the VMM syscall handler
 ///
 /// # Arguments
 ///
 /// * `handle_val` - VCPU handle value
-/// * `packet_out` - User pointer to store packet
 ///
 /// # Returns
 ///
 /// * On success: 0
 /// * On error: Negative error code
-pub fn sys_vcpu_resume_impl(handle_val: u32, packet_out: usize) -> SyscallRet {
-    log_debug!("sys_vcpu_resume: handle={:#x}", handle_val);
-
-    // TODO: Implement proper VCPU resume
-    // For now, just log
-    log_info!("VCPU resume: handle={:#x}", handle_val);
-
-    // Zero packet for now
-    if packet_out != 0 {
-        let packet = 0u64;
-        let user_ptr = UserPtr::<u8>::new(packet_out);
-        unsafe {
-            if let Err(err) = copy_to_user(user_ptr, &packet as *const u64 as *const u8, 8) {
-                log_error!("sys_vcpu_resume: copy_to_user failed: {:?}", err);
-                return err_to_ret(err.into());
-            }
+pub fn sys_vcpu_kick_impl(handle_val: u32) -> SyscallRet {
+    log_debug!("sys_vcpu_kick: handle={:#x}", handle_val);
+
+    match lookup_vcpu(handle_val) {
+        Ok(vcpu) => {
+            vcpu.kick();
+            ok_to_ret(0)
         }
+        Err(err) => err_to_ret(err),
     }
-
-    ok_to_ret(0)
 }
 
 /// ============================================================================
@@ -369,10 +398,10 @@ pub fn sys_vcpu_interrupt_impl(handle_val: u32, vector: u32) -> SyscallRet {
         handle_val, vector
     );
 
-    // TODO: Implement proper VCPU interrupt
-    log_info!("VCPU interrupt: handle={:#x} vector={}", handle_val, vector);
-
-    ok_to_ret(0)
+    match lookup_vcpu(handle_val).and_then(|vcpu| vcpu.interrupt(vector)) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -410,9 +439,10 @@ pub fn sys_vcpu_read_state_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Implement proper state reading
-    // For now, zero out the buffer
-    let state = VcpuState { regs: [0; 64] };
+    let state = match lookup_vcpu(handle_val).and_then(|vcpu| vcpu.read_state(kind)) {
+        Ok(state) => state,
+        Err(err) => return err_to_ret(err),
+    };
 
     // Copy to user
     let user_ptr = UserPtr::new(buffer);
@@ -438,6 +468,8 @@ pub fn sys_vcpu_read_state_impl(
 
 /// Write VCPU state syscall handler
 ///
+/// Registers past `buffer_size` keep their current values.
+///
 /// # Arguments
 ///
 /// * `handle_val` - VCPU handle value
@@ -467,8 +499,14 @@ pub fn sys_vcpu_write_state_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // Allocate buffer for state
-    let mut state = VcpuState { regs: [0; 64] };
+    let vcpu = match lookup_vcpu(handle_val) {
+        Ok(vcpu) => vcpu,
+        Err(err) => return err_to_ret(err),
+    };
+    let mut state = match vcpu.read_state(kind) {
+        Ok(state) => state,
+        Err(err) => return err_to_ret(err),
+    };
 
     // Copy from user
     let user_ptr = UserPtr::new(buffer);
@@ -483,10 +521,10 @@ pub fn sys_vcpu_write_state_impl(
         }
     }
 
-    // TODO: Implement proper state writing
-    log_info!("VCPU write state: handle={:#x} kind={}", handle_val, kind);
-
-    ok_to_ret(0)
+    match vcpu.write_state(kind, &state) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -496,8 +534,8 @@ pub fn sys_vcpu_write_state_impl(
 /// Get hypervisor subsystem statistics
 pub fn get_stats() -> HypervisorStats {
     HypervisorStats {
-        total_guests: 0, // TODO: Track guests
-        total_vcpus: 0,  // TODO: Track VCPUs
+        total_guests: GUEST_REGISTRY.lock().len(),
+        total_vcpus: VCPU_REGISTRY.lock().len(),
     }
 }
 
@@ -520,7 +558,7 @@ pub struct HypervisorStats {
 pub fn init() {
     log_info!("Hypervisor syscall subsystem initialized");
     log_info!("  Max guests: {}", MAX_GUESTS);
-    log_info!("  Max VCPUs: {}", MAX_VCPUS);
+    log_info!("  Max VCPUs per guest: {}", MAX_VCPUS);
 }
 
 /// ============================================================================
@@ -533,34 +571,60 @@ mod tests {
 
     #[test]
     fn test_guest_create() {
-        let result = sys_guest_create_impl(0, 0, 0, 0);
+        let result = sys_guest_create_impl(resource::test_root_handle(), 0);
         assert!(result >= 0);
     }
 
     #[test]
     fn test_guest_create_invalid_options() {
-        let result = sys_guest_create_impl(0, 0xFF, 0, 0);
+        let result = sys_guest_create_impl(resource::test_root_handle(), 0xFF);
         assert!(result < 0);
     }
 
     #[test]
     fn test_guest_create_invalid_resource() {
-        let result = sys_guest_create_impl(999, 0, 0, 0);
+        let result = sys_guest_create_impl(999, 0);
         assert!(result < 0);
     }
 
     #[test]
-    fn test_vcpu_create() {
-        let result = sys_vcpu_create_impl(0, 0, 0, 0);
-        assert!(result >= 0);
+    fn test_guest_op_invalid() {
+        let guest = sys_guest_create_impl(resource::test_root_handle(), 0) as u32;
+        assert_eq!(sys_guest_op_impl(guest, 0x7F, 0, 0, 0, 0x1000), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_guest_op_impl(guest, guest_op::UNMAP, 0, 0, 0, 0x1000), err_to_ret(RX_ERR_NOT_FOUND));
+        assert_eq!(sys_guest_op_impl(0, guest_op::UNMAP, 0, 0, 0, 0x1000), err_to_ret(RX_ERR_BAD_HANDLE));
+    }
+
+    #[test]
+    fn test_guest_set_trap() {
+        let guest = sys_guest_create_impl(resource::test_root_handle(), 0) as u32;
+        assert_eq!(sys_guest_set_trap_impl(guest, trap_kind::MMIO, 0x9000_0000, 0x1000, 0, 1), 0);
+        assert_eq!(
+            sys_guest_set_trap_impl(guest, trap_kind::MMIO, 0x9000_0000, 0x1000, 0, 2),
+            err_to_ret(RX_ERR_ALREADY_EXISTS)
+        );
+        // BELL traps need a port
+        assert!(sys_guest_set_trap_impl(guest, trap_kind::BELL, 0x9000_1000, 0x1000, 0, 3) < 0);
+    }
+
+    #[test]
+    fn test_vcpu_create_bad_guest() {
+        let result = sys_vcpu_create_impl(0, 0, 0);
+        assert_eq!(result, err_to_ret(RX_ERR_BAD_HANDLE));
     }
 
     #[test]
     fn test_vcpu_create_invalid_options() {
-        let result = sys_vcpu_create_impl(0, 0xFF, 0, 0);
+        let result = sys_vcpu_create_impl(0, 0xFF, 0);
         assert!(result < 0);
     }
 
+    #[test]
+    fn test_vcpu_bad_handle() {
+        assert_eq!(sys_vcpu_kick_impl(0), err_to_ret(RX_ERR_BAD_HANDLE));
+        assert_eq!(sys_vcpu_interrupt_impl(0, 32), err_to_ret(RX_ERR_BAD_HANDLE));
+    }
+
     #[test]
     fn test_vcpu_state_size() {
         assert!(core::mem::size_of::<VcpuState>() >= 512);
@@ -0,0 +1,138 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Counter System Calls
+//!
+//! This module exposes the kernel counters to userspace.
+//!
+//! # Syscalls Implemented
+//!
+//! - `rx_kcounter_read` - Snapshot every counter's name and value
+//!
+//! # Design
+//!
+//! - Requires the root resource
+//! - Follows the `rx_object_get_info` convention: the caller passes a
+//!   buffer size in bytes and gets back the number of records written
+//!   and the number available, so it can retry with a larger buffer
+//! - Records come out sorted by name, and a counter keeps its position
+//!   for the life of the kernel, so consecutive snapshots line up
+
+use crate::kernel::lib::counters::{self, KCOUNTER_MAX_NAME};
+use crate::kernel::syscalls::system::{validate_resource, ResourceKind};
+use crate::kernel::usercopy::{copy_to_user, UserPtr};
+use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+
+// Import logging macros
+use crate::{log_debug, log_error};
+
+/// One counter as returned to userspace
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KcounterRecord {
+    /// NUL-padded name
+    pub name: [u8; KCOUNTER_MAX_NAME],
+
+    /// `KcounterType` of the counter
+    pub kind: u32,
+
+    /// Reserved, zero
+    pub reserved: u32,
+
+    /// Value combined across CPUs
+    pub value: i64,
+}
+
+impl KcounterRecord {
+    /// Record for the counter at `index`
+    fn new(index: usize) -> Self {
+        let desc = &counters::descriptors()[index];
+        let mut name = [0u8; KCOUNTER_MAX_NAME];
+        let bytes = desc.name().as_bytes();
+        let len = bytes.len().min(KCOUNTER_MAX_NAME - 1);
+        name[..len].copy_from_slice(&bytes[..len]);
+
+        Self {
+            name,
+            kind: desc.kind() as u32,
+            reserved: 0,
+            value: counters::value(index),
+        }
+    }
+}
+
+/// ============================================================================
+/// Syscall: Kcounter Read
+/// ============================================================================
+
+/// Copy a snapshot of the kernel counters to a user buffer
+///
+/// # Arguments
+///
+/// * `resource_handle` - Root resource handle
+/// * `buffer` - User buffer for `KcounterRecord`s
+/// * `buffer_size` - Size of `buffer` in bytes
+/// * `actual_out` - User pointer to store the number of records written
+/// * `avail_out` - User pointer to store the number of counters
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_kcounter_read_impl(
+    resource_handle: u32,
+    buffer: usize,
+    buffer_size: usize,
+    actual_out: usize,
+    avail_out: usize,
+) -> SyscallRet {
+    log_debug!("sys_kcounter_read: resource={:#x} size={}", resource_handle, buffer_size);
+
+    if let Err(err) = validate_resource(resource_handle, ResourceKind::Root) {
+        log_error!("sys_kcounter_read: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let record_size = core::mem::size_of::<KcounterRecord>();
+    let avail = counters::count();
+    let actual = avail.min(buffer_size / record_size);
+
+    for index in 0..actual {
+        let record = KcounterRecord::new(index);
+        let user_ptr = UserPtr::<u8>::new(buffer + index * record_size);
+        if let Err(err) = unsafe {
+            copy_to_user(user_ptr, &record as *const KcounterRecord as *const u8, record_size)
+        } {
+            log_error!("sys_kcounter_read: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    for (out, count) in [(actual_out, actual), (avail_out, avail)] {
+        if out == 0 {
+            continue;
+        }
+        let user_ptr = UserPtr::<u8>::new(out);
+        if let Err(err) = unsafe {
+            copy_to_user(user_ptr, &count as *const usize as *const u8, core::mem::size_of::<usize>())
+        } {
+            log_error!("sys_kcounter_read: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_layout() {
+        assert_eq!(core::mem::size_of::<KcounterRecord>(), 64);
+    }
+}
@@ -38,6 +38,11 @@ use crate::rustux::types::err::*;
 // Import logging macros
 use crate::{log_debug, log_error, log_info, log_trace};
 
+use crate::kernel::lib::ktrace;
+use crate::KCOUNTER;
+
+KCOUNTER!(SYSCALLS, "kernel.syscalls");
+
 // Syscall implementations
 pub mod vmo;
 pub mod channel;
@@ -63,6 +68,7 @@ pub mod resource;
 pub mod test;
 pub mod exception;
 pub mod profile;
+pub mod counter;
 pub mod debug;
 pub mod exceptions;
 pub mod rustux;
@@ -70,6 +76,10 @@ pub mod ddk_x86;
 pub mod ddk_arm64;
 pub mod ddk;
 pub mod ddk_pci;
+pub mod cprng;
+pub mod clock;
+pub mod kcounter;
+pub mod stats;
 
 /// ============================================================================
 /// Syscall Numbers (Stable v1)
@@ -79,166 +89,19 @@ pub mod ddk_pci;
 ///
 /// These numbers are frozen as part of the stable ABI v1.
 /// DO NOT change existing numbers - only append new syscalls.
+///
+/// The variants, `from_raw`, `name` and `dispatch` are generated from
+/// `abi/syscalls.abi`, which libsys numbers its syscalls from as well. A
+/// new syscall goes in that file, with its handler as `sys_<name>` below.
+#[rustux_macros::syscall_abi("abi/syscalls.abi", kernel)]
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum SyscallNumber {
-    // Process & Thread (0x001-0x00F)
-
-    /// Create new process under job
-    rx_process_create = 0x01,
-
-    /// Begin process execution
-    rx_process_start = 0x02,
-
-    /// Create thread in process
-    rx_thread_create = 0x03,
-
-    /// Begin thread execution
-    rx_thread_start = 0x04,
-
-    /// Terminate thread
-    rx_thread_exit = 0x05,
-
-    /// Terminate process
-    rx_process_exit = 0x06,
-
-    /// Close handle
-    rx_handle_close = 0x07,
-
-    // Memory / VMO (0x010-0x01F)
-
-    /// Create virtual memory object
-    rx_vmo_create = 0x10,
-
-    /// Read from VMO
-    rx_vmo_read = 0x11,
-
-    /// Write to VMO
-    rx_vmo_write = 0x12,
-
-    /// COW clone VMO
-    rx_vmo_clone = 0x13,
-
-    /// Map VMO into address space
-    rx_vmar_map = 0x14,
-
-    /// Unmap region
-    rx_vmar_unmap = 0x15,
-
-    /// Change protection
-    rx_vmar_protect = 0x16,
-
-    // IPC & Sync (0x020-0x02F)
-
-    /// Create message channel
-    rx_channel_create = 0x20,
-
-    /// Write message + handles
-    rx_channel_write = 0x21,
-
-    /// Read message + handles
-    rx_channel_read = 0x22,
-
-    /// Create event object
-    rx_event_create = 0x23,
-
-    /// Create event pair
-    rx_eventpair_create = 0x24,
-
-    /// Signal object
-    rx_object_signal = 0x25,
-
-    /// Wait on single object
-    rx_object_wait_one = 0x26,
-
-    /// Wait on multiple objects
-    rx_object_wait_many = 0x27,
-
-    // Jobs & Handles (0x030-0x03F)
-
-    /// Create job under parent
-    rx_job_create = 0x30,
-
-    /// Duplicate handle with rights
-    rx_handle_duplicate = 0x31,
-
-    /// Transfer handle to process
-    rx_handle_transfer = 0x32,
-
-    // Time (0x040-0x04F)
-
-    /// Get monotonic/realtime
-    rx_clock_get = 0x40,
-
-    /// Create timer
-    rx_timer_create = 0x41,
-
-    /// Arm timer
-    rx_timer_set = 0x42,
-
-    /// Cancel timer
-    rx_timer_cancel = 0x43,
-
     /// Unknown/invalid syscall number
     Unknown = 0xFFFF,
 }
 
-impl SyscallNumber {
-    /// Convert from raw number
-    pub const fn from_raw(n: u32) -> Self {
-        // This is a simplified conversion
-        // In a real implementation, we'd have a match statement
-        // but const fn limits us
-        if n <= 0x43 {
-            // Valid syscall number (simplified)
-            Self::from_raw_unchecked(n)
-        } else {
-            Self::Unknown
-        }
-    }
-
-    const fn from_raw_unchecked(n: u32) -> Self {
-        unsafe { core::mem::transmute(n) }
-    }
-
-    /// Get the syscall name
-    pub const fn name(&self) -> &'static str {
-        match self {
-            Self::rx_process_create => "rx_process_create",
-            Self::rx_process_start => "rx_process_start",
-            Self::rx_thread_create => "rx_thread_create",
-            Self::rx_thread_start => "rx_thread_start",
-            Self::rx_thread_exit => "rx_thread_exit",
-            Self::rx_process_exit => "rx_process_exit",
-            Self::rx_handle_close => "rx_handle_close",
-            Self::rx_vmo_create => "rx_vmo_create",
-            Self::rx_vmo_read => "rx_vmo_read",
-            Self::rx_vmo_write => "rx_vmo_write",
-            Self::rx_vmo_clone => "rx_vmo_clone",
-            Self::rx_vmar_map => "rx_vmar_map",
-            Self::rx_vmar_unmap => "rx_vmar_unmap",
-            Self::rx_vmar_protect => "rx_vmar_protect",
-            Self::rx_channel_create => "rx_channel_create",
-            Self::rx_channel_write => "rx_channel_write",
-            Self::rx_channel_read => "rx_channel_read",
-            Self::rx_event_create => "rx_event_create",
-            Self::rx_eventpair_create => "rx_eventpair_create",
-            Self::rx_object_signal => "rx_object_signal",
-            Self::rx_object_wait_one => "rx_object_wait_one",
-            Self::rx_object_wait_many => "rx_object_wait_many",
-            Self::rx_job_create => "rx_job_create",
-            Self::rx_handle_duplicate => "rx_handle_duplicate",
-            Self::rx_handle_transfer => "rx_handle_transfer",
-            Self::rx_clock_get => "rx_clock_get",
-            Self::rx_timer_create => "rx_timer_create",
-            Self::rx_timer_set => "rx_timer_set",
-            Self::rx_timer_cancel => "rx_timer_cancel",
-            Self::Unknown => "unknown",
-        }
-    }
-}
-
 /// ============================================================================
 /// Syscall Arguments
 /// ============================================================================
@@ -288,8 +151,10 @@ pub type SyscallRet = isize;
 
 /// Convert error code to negative return value
 #[inline]
+///
+/// Status codes are already negative, so they pass through unchanged.
 pub const fn err_to_ret(err: Status) -> SyscallRet {
-    -(err as SyscallRet)
+    err as SyscallRet
 }
 
 /// Convert success value to return value
@@ -322,6 +187,7 @@ pub const fn ok_to_ret(val: usize) -> SyscallRet {
 #[no_mangle]
 pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
     let num = SyscallNumber::from_raw(args.number);
+    SYSCALLS.add(1);
 
     log_trace!(
         "syscall: num={} ({}) args=[{:#x}, {:#x}, {:#x}, {:#x}, {:#x}, {:#x}]",
@@ -335,52 +201,21 @@ pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
         args.args[5]
     );
 
+    ktrace::write(ktrace::TAG_SYSCALL_ENTER, args.number as u16, args.args[0] as u64, args.args[1] as u64);
+    let start = crate::kernel::timer::current_time();
+
     // Dispatch to handler
-    match num {
-        // Process & Thread
-        SyscallNumber::rx_process_create => sys_process_create(args),
-        SyscallNumber::rx_process_start => sys_process_start(args),
-        SyscallNumber::rx_thread_create => sys_thread_create(args),
-        SyscallNumber::rx_thread_start => sys_thread_start(args),
-        SyscallNumber::rx_thread_exit => sys_thread_exit(args),
-        SyscallNumber::rx_process_exit => sys_process_exit(args),
-        SyscallNumber::rx_handle_close => sys_handle_close(args),
-
-        // Memory / VMO
-        SyscallNumber::rx_vmo_create => sys_vmo_create(args),
-        SyscallNumber::rx_vmo_read => sys_vmo_read(args),
-        SyscallNumber::rx_vmo_write => sys_vmo_write(args),
-        SyscallNumber::rx_vmo_clone => sys_vmo_clone(args),
-        SyscallNumber::rx_vmar_map => sys_vmar_map(args),
-        SyscallNumber::rx_vmar_unmap => sys_vmar_unmap(args),
-        SyscallNumber::rx_vmar_protect => sys_vmar_protect(args),
-
-        // IPC & Sync
-        SyscallNumber::rx_channel_create => sys_channel_create(args),
-        SyscallNumber::rx_channel_write => sys_channel_write(args),
-        SyscallNumber::rx_channel_read => sys_channel_read(args),
-        SyscallNumber::rx_event_create => sys_event_create(args),
-        SyscallNumber::rx_eventpair_create => sys_eventpair_create(args),
-        SyscallNumber::rx_object_signal => sys_object_signal(args),
-        SyscallNumber::rx_object_wait_one => sys_object_wait_one(args),
-        SyscallNumber::rx_object_wait_many => sys_object_wait_many(args),
-
-        // Jobs & Handles
-        SyscallNumber::rx_job_create => sys_job_create(args),
-        SyscallNumber::rx_handle_duplicate => sys_handle_duplicate(args),
-        SyscallNumber::rx_handle_transfer => sys_handle_transfer(args),
-
-        // Time
-        SyscallNumber::rx_clock_get => sys_clock_get(args),
-        SyscallNumber::rx_timer_create => sys_timer_create(args),
-        SyscallNumber::rx_timer_set => sys_timer_set(args),
-        SyscallNumber::rx_timer_cancel => sys_timer_cancel(args),
-
-        SyscallNumber::Unknown => {
+    let ret = match num.dispatch(args) {
+        Some(ret) => ret,
+        None => {
             log_error!("Unknown syscall: {}", args.number);
             err_to_ret(RX_ERR_NOT_SUPPORTED)
         }
-    }
+    };
+
+    ktrace::write(ktrace::TAG_SYSCALL_EXIT, args.number as u16, ret as u64, 0);
+    stats::record(args.number, ret, crate::kernel::timer::current_time().saturating_sub(start));
+    ret
 }
 
 /// ============================================================================
@@ -443,9 +278,63 @@ fn sys_process_exit(args: SyscallArgs) -> SyscallRet {
 
 fn sys_handle_close(args: SyscallArgs) -> SyscallRet {
     let handle = args.arg(0) as u32;
-    // TODO: Implement handle close
-    log_debug!("sys_handle_close: handle={}", handle);
-    ok_to_ret(0)
+    handle_ops::sys_handle_close_impl(handle)
+}
+
+fn sys_thread_set_affinity(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let mask = args.arg(1) as u64;
+    task::sys_thread_set_affinity_impl(handle, mask)
+}
+
+fn sys_thread_get_affinity(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let mask_out = args.arg(1);
+    task::sys_thread_get_affinity_impl(handle, mask_out)
+}
+
+fn sys_thread_read_state(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let kind = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    task::sys_thread_read_state_impl(handle, kind, buffer, buffer_size)
+}
+
+fn sys_thread_write_state(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let kind = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    task::sys_thread_write_state_impl(handle, kind, buffer, buffer_size)
+}
+
+fn sys_thread_resume(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    task::sys_thread_resume_impl(handle, options)
+}
+
+fn sys_thread_write_coredump(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    task::sys_thread_write_coredump_impl(handle, options)
+}
+
+fn sys_process_read_memory(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let vaddr = args.arg(1) as u64;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    task::sys_process_read_memory_impl(handle, vaddr, buffer, buffer_size)
+}
+
+fn sys_process_write_memory(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let vaddr = args.arg(1) as u64;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    task::sys_process_write_memory_impl(handle, vaddr, buffer, buffer_size)
 }
 
 // Memory / VMO syscalls
@@ -477,6 +366,20 @@ fn sys_vmo_clone(args: SyscallArgs) -> SyscallRet {
     let size = args.arg(2);
     vmo::sys_vmo_clone_impl(handle, offset, size)
 }
+
+fn sys_vmo_set_size(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let size = args.arg(1);
+    vmo::sys_vmo_set_size_impl(handle, size)
+}
+
+fn sys_vmo_op_range(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let op = args.arg(1) as u32;
+    let offset = args.arg(2);
+    let size = args.arg(3);
+    vmo::sys_vmo_op_range_impl(handle, op, offset, size)
+}
 fn sys_vmar_map(args: SyscallArgs) -> SyscallRet {
     let vmar = args.arg(0) as u32;
     let options = args.arg(1) as u32;
@@ -528,6 +431,35 @@ fn sys_channel_read(args: SyscallArgs) -> SyscallRet {
     let handles_capacity = args.arg(5);
     channel::sys_channel_read_impl(handle, options, user_data, data_capacity, user_handles, handles_capacity)
 }
+
+fn sys_channel_write_etc(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let user_data = args.arg(2);
+    let data_size = args.arg(3);
+    let user_dispositions = args.arg(4);
+    let handle_count = args.arg(5);
+    channel::sys_channel_write_etc_impl(handle, options, user_data, data_size, user_dispositions, handle_count)
+}
+
+fn sys_channel_read_etc(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let user_data = args.arg(2);
+    let data_capacity = args.arg(3);
+    let user_infos = args.arg(4);
+    let handles_capacity = args.arg(5);
+    channel::sys_channel_read_etc_impl(handle, options, user_data, data_capacity, user_infos, handles_capacity)
+}
+
+fn sys_channel_call_etc(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let deadline = args.arg(2) as u64;
+    let user_args = args.arg(3);
+    channel::sys_channel_call_etc_impl(handle, options, deadline, user_args)
+}
+
 fn sys_event_create(args: SyscallArgs) -> SyscallRet {
     let options = args.arg(0) as u32;
     event::sys_event_create_impl(options)
@@ -539,8 +471,39 @@ fn sys_eventpair_create(args: SyscallArgs) -> SyscallRet {
 
 fn sys_object_signal(args: SyscallArgs) -> SyscallRet {
     let handle = args.arg(0) as u32;
-    let options = args.arg(1) as u32;
-    event::sys_object_signal_impl(handle, options)
+    let clear_mask = args.arg(1) as u64;
+    let set_mask = args.arg(2) as u64;
+    event::sys_object_signal_impl(handle, clear_mask, set_mask)
+}
+
+fn sys_object_signal_peer(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let clear_mask = args.arg(1) as u64;
+    let set_mask = args.arg(2) as u64;
+    event::sys_object_signal_peer_impl(handle, clear_mask, set_mask)
+}
+
+fn sys_counter_create(args: SyscallArgs) -> SyscallRet {
+    let options = args.arg(0) as u32;
+    counter::sys_counter_create_impl(options)
+}
+
+fn sys_counter_add(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let amount = args.arg(1) as i64;
+    counter::sys_counter_add_impl(handle, amount)
+}
+
+fn sys_counter_read(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let value_out = args.arg(1);
+    counter::sys_counter_read_impl(handle, value_out)
+}
+
+fn sys_counter_write(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let value = args.arg(1) as i64;
+    counter::sys_counter_write_impl(handle, value)
 }
 
 fn sys_object_wait_one(args: SyscallArgs) -> SyscallRet {
@@ -558,6 +521,16 @@ fn sys_object_wait_many(args: SyscallArgs) -> SyscallRet {
     object_wait::sys_object_wait_many_impl(user_items, count, deadline)
 }
 
+fn sys_object_get_info(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let topic = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    let actual_out = args.arg(4);
+    let avail_out = args.arg(5);
+    object::sys_object_get_info_impl(handle, topic, buffer, buffer_size, actual_out, avail_out)
+}
+
 // Jobs & Handles syscalls
 fn sys_job_create(args: SyscallArgs) -> SyscallRet {
     let parent = args.arg(0) as u32;
@@ -579,18 +552,81 @@ fn sys_handle_transfer(args: SyscallArgs) -> SyscallRet {
     handle_ops::sys_handle_transfer_impl(handle, rights, options)
 }
 
+fn sys_profile_create(args: SyscallArgs) -> SyscallRet {
+    let root_job = args.arg(0) as u32;
+    let info = args.arg(1);
+    let out = args.arg(2);
+    profile::sys_profile_create_impl(root_job, info, out)
+}
+
+fn sys_object_set_profile(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let profile = args.arg(1) as u32;
+    let options = args.arg(2) as u32;
+    profile::sys_object_set_profile_impl(handle, profile, options)
+}
+
+fn sys_handle_close_many(args: SyscallArgs) -> SyscallRet {
+    let handles = args.arg(0);
+    let num_handles = args.arg(1);
+    handle_ops::sys_handle_close_many_impl(handles, num_handles)
+}
+
+fn sys_object_get_child(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let koid = args.arg(1) as u64;
+    let rights = args.arg(2) as u32;
+    object::sys_object_get_child_impl(handle, koid, rights)
+}
+
+fn sys_task_create_exception_channel(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    task::sys_task_create_exception_channel_impl(handle, options)
+}
+
 // Time syscalls
 fn sys_clock_get(args: SyscallArgs) -> SyscallRet {
     let clock_id = args.arg(0) as u32;
-    // Return current time in nanoseconds
-    // TODO: Implement proper clock
-    if clock_id == 0 {
-        // CLOCK_MONOTONIC
-        let time = 0; // Placeholder
-        ok_to_ret(time as usize)
-    } else {
-        err_to_ret(RX_ERR_INVALID_ARGS)
-    }
+    clock::sys_clock_get_impl(clock_id)
+}
+
+fn sys_clock_create(args: SyscallArgs) -> SyscallRet {
+    let options = args.arg(0) as u64;
+    let backstop = args.arg(1) as i64;
+    let clock_out = args.arg(2);
+    clock::sys_clock_create_impl(options, backstop, clock_out)
+}
+
+fn sys_clock_read(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let now_out = args.arg(1);
+    clock::sys_clock_read_impl(handle, now_out)
+}
+
+fn sys_clock_get_details(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let details_out = args.arg(1);
+    let details_size = args.arg(2);
+    clock::sys_clock_get_details_impl(handle, details_out, details_size)
+}
+
+fn sys_clock_update(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let update = args.arg(1);
+    let update_size = args.arg(2);
+    clock::sys_clock_update_impl(handle, update, update_size)
+}
+
+fn sys_utc_reference_get(_args: SyscallArgs) -> SyscallRet {
+    clock::sys_utc_reference_get_impl()
+}
+
+fn sys_utc_reference_swap(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let handle = args.arg(1) as u32;
+    let prev_out = args.arg(2);
+    clock::sys_utc_reference_swap_impl(resource, handle, prev_out)
 }
 
 fn sys_timer_create(args: SyscallArgs) -> SyscallRet {
@@ -611,6 +647,493 @@ fn sys_timer_cancel(args: SyscallArgs) -> SyscallRet {
     timer::sys_timer_cancel_impl(handle)
 }
 
+// Driver / DDK syscalls
+fn sys_vmo_create_contiguous(args: SyscallArgs) -> SyscallRet {
+    let bti = args.arg(0) as u32;
+    let size = args.arg(1);
+    let alignment_log2 = args.arg(2) as u32;
+    let vmo_out = args.arg(3);
+    ddk::sys_vmo_create_contiguous_impl(bti, size, alignment_log2, vmo_out)
+}
+
+fn sys_vmo_create_physical(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let paddr = args.arg(1) as u64;
+    let size = args.arg(2);
+    let vmo_out = args.arg(3);
+    ddk::sys_vmo_create_physical_impl(resource, paddr, size, vmo_out)
+}
+
+fn sys_framebuffer_get_info(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let info_out = args.arg(1);
+    let options = args.arg(2) as u32;
+    ddk::sys_framebuffer_get_info_impl(resource, info_out, options)
+}
+
+fn sys_ioports_request(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let io_addr = args.arg(1) as u16;
+    let len = args.arg(2) as u32;
+    ddk::sys_ioports_request_impl(resource, io_addr, len)
+}
+
+fn sys_bti_create(args: SyscallArgs) -> SyscallRet {
+    let iommu = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let bti_id = args.arg(2) as u64;
+    let bti_out = args.arg(3);
+    ddk::sys_bti_create_impl(iommu, options, bti_id, bti_out)
+}
+
+fn sys_bti_pin(args: SyscallArgs) -> SyscallRet {
+    let bti = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let vmo = args.arg(2) as u32;
+    let offset = args.arg(3) as u64;
+    let size = args.arg(4) as u64;
+
+    // Output pointers don't fit in the remaining register
+    let mut out = ddk::BtiPinBuffers::default();
+    let user_ptr = crate::kernel::usercopy::UserPtr::<u8>::new(args.arg(5));
+    unsafe {
+        if let Err(err) = crate::kernel::usercopy::copy_from_user(
+            &mut out as *mut ddk::BtiPinBuffers as *mut u8,
+            user_ptr,
+            core::mem::size_of::<ddk::BtiPinBuffers>(),
+        ) {
+            return err_to_ret(err.into());
+        }
+    }
+    ddk::sys_bti_pin_impl(bti, options, vmo, offset, size, out.addrs, out.addrs_count, out.pmt_out)
+}
+
+fn sys_pmt_unpin(args: SyscallArgs) -> SyscallRet {
+    let pmt = args.arg(0) as u32;
+    ddk::sys_pmt_unpin_impl(pmt)
+}
+
+fn sys_interrupt_create(args: SyscallArgs) -> SyscallRet {
+    let src_obj = args.arg(0) as u32;
+    let src_num = args.arg(1) as u32;
+    let options = args.arg(2) as u32;
+    let handle_out = args.arg(3);
+    ddk::sys_interrupt_create_impl(src_obj, src_num, options, handle_out)
+}
+
+fn sys_interrupt_wait(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let timestamp_out = args.arg(1);
+    ddk::sys_interrupt_wait_impl(handle, timestamp_out)
+}
+
+fn sys_interrupt_ack(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    ddk::sys_interrupt_ack_impl(handle)
+}
+
+fn sys_interrupt_destroy(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    ddk::sys_interrupt_destroy_impl(handle)
+}
+
+fn sys_interrupt_trigger(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let timestamp = args.arg(2) as u64;
+    ddk::sys_interrupt_trigger_impl(handle, options, timestamp)
+}
+
+fn sys_vmo_set_cache_policy(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let policy = args.arg(1) as u32;
+    vmo::sys_vmo_set_cache_policy_impl(handle, policy)
+}
+
+fn sys_vmo_transfer_data(args: SyscallArgs) -> SyscallRet {
+    let dst = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let offset = args.arg(2);
+    let length = args.arg(3);
+    let src = args.arg(4) as u32;
+    let src_offset = args.arg(5);
+    vmo::sys_vmo_transfer_data_impl(dst, options, offset, length, src, src_offset)
+}
+
+fn sys_iommu_create(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let type_ = args.arg(1) as u32;
+    let desc = args.arg(2);
+    let desc_size = args.arg(3);
+    let iommu_out = args.arg(4);
+    ddk::sys_iommu_create_impl(resource, type_, desc, desc_size, iommu_out)
+}
+
+fn sys_bti_release_quarantine(args: SyscallArgs) -> SyscallRet {
+    let bti = args.arg(0) as u32;
+    ddk::sys_bti_release_quarantine_impl(bti)
+}
+
+fn sys_pci_get_nth_device(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let index = args.arg(1) as u32;
+    let info_out = args.arg(2);
+    let handle_out = args.arg(3);
+    ddk_pci::sys_pci_get_nth_device_impl(resource, index, info_out, handle_out)
+}
+
+fn sys_pci_config_read(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let offset = args.arg(1) as u16;
+    let width = args.arg(2);
+    let val_out = args.arg(3);
+    ddk_pci::sys_pci_config_read_impl(handle, offset, width, val_out)
+}
+
+fn sys_pci_config_write(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let offset = args.arg(1) as u16;
+    let width = args.arg(2);
+    let val = args.arg(3) as u32;
+    ddk_pci::sys_pci_config_write_impl(handle, offset, width, val)
+}
+
+fn sys_pci_enable_bus_master(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let enable = args.arg(1) != 0;
+    ddk_pci::sys_pci_enable_bus_master_impl(handle, enable)
+}
+
+fn sys_pci_get_bar(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let bar_num = args.arg(1) as u32;
+    let bar_out = args.arg(2);
+    let handle_out = args.arg(3);
+    ddk_pci::sys_pci_get_bar_impl(handle, bar_num, bar_out, handle_out)
+}
+
+fn sys_pci_map_interrupt(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let which_irq = args.arg(1) as i32;
+    let handle_out = args.arg(2);
+    ddk_pci::sys_pci_map_interrupt_impl(handle, which_irq, handle_out)
+}
+
+fn sys_pci_query_irq_mode(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let mode = args.arg(1) as u32;
+    let max_irqs_out = args.arg(2);
+    ddk_pci::sys_pci_query_irq_mode_impl(handle, mode, max_irqs_out)
+}
+
+fn sys_pci_set_irq_mode(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let mode = args.arg(1) as u32;
+    let count = args.arg(2) as u32;
+    ddk_pci::sys_pci_set_irq_mode_impl(handle, mode, count)
+}
+
+fn sys_fifo_create(args: SyscallArgs) -> SyscallRet {
+    let count = args.arg(0);
+    let elem_size = args.arg(1);
+    let options = args.arg(2) as u32;
+    let handle0_out = args.arg(3);
+    let handle1_out = args.arg(4);
+    fifo::sys_fifo_create_impl(count, elem_size, options, handle0_out, handle1_out)
+}
+
+fn sys_fifo_write(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let elem_size = args.arg(1);
+    let data = args.arg(2);
+    let count = args.arg(3);
+    let actual_count_out = args.arg(4);
+    fifo::sys_fifo_write_impl(handle, elem_size, data, count, actual_count_out)
+}
+
+fn sys_fifo_read(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let elem_size = args.arg(1);
+    let data = args.arg(2);
+    let count = args.arg(3);
+    let actual_count_out = args.arg(4);
+    fifo::sys_fifo_read_impl(handle, elem_size, data, count, actual_count_out)
+}
+
+fn sys_hypervisor_create(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    hypervisor::sys_guest_create_impl(resource, options)
+}
+
+fn sys_hypervisor_op(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let op = args.arg(1) as u32;
+    let addr = args.arg(2) as u64;
+    let vmo = args.arg(3) as u32;
+    let vmo_offset = args.arg(4);
+    let len = args.arg(5);
+    hypervisor::sys_guest_op_impl(handle, op, addr, vmo, vmo_offset, len)
+}
+
+fn sys_guest_set_trap(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let kind = args.arg(1) as u32;
+    let addr = args.arg(2) as u64;
+    let size = args.arg(3);
+    let port = args.arg(4) as u32;
+    let key = args.arg(5) as u64;
+    hypervisor::sys_guest_set_trap_impl(handle, kind, addr, size, port, key)
+}
+
+fn sys_vcpu_create(args: SyscallArgs) -> SyscallRet {
+    let guest = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let entry = args.arg(2) as u64;
+    hypervisor::sys_vcpu_create_impl(guest, options, entry)
+}
+
+fn sys_vcpu_enter(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let packet_out = args.arg(1);
+    hypervisor::sys_vcpu_enter_impl(handle, packet_out)
+}
+
+fn sys_vcpu_kick(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    hypervisor::sys_vcpu_kick_impl(handle)
+}
+
+fn sys_vcpu_interrupt(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let vector = args.arg(1) as u32;
+    hypervisor::sys_vcpu_interrupt_impl(handle, vector)
+}
+
+fn sys_vcpu_read_state(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let kind = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    hypervisor::sys_vcpu_read_state_impl(handle, kind, buffer, buffer_size)
+}
+
+fn sys_vcpu_write_state(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let kind = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    hypervisor::sys_vcpu_write_state_impl(handle, kind, buffer, buffer_size)
+}
+
+fn sys_system_powerctl(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let cmd = args.arg(1) as u32;
+    let arg = args.arg(2);
+    system::sys_system_powerctl_impl(resource, cmd, arg)
+}
+
+fn sys_cprng_draw(args: SyscallArgs) -> SyscallRet {
+    let buffer = args.arg(0);
+    let len = args.arg(1);
+    cprng::sys_cprng_draw_impl(buffer, len)
+}
+
+fn sys_cprng_add_entropy(args: SyscallArgs) -> SyscallRet {
+    let buffer = args.arg(0);
+    let len = args.arg(1);
+    cprng::sys_cprng_add_entropy_impl(buffer, len)
+}
+
+fn sys_system_crashlog_read(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let offset = args.arg(1);
+    let buffer = args.arg(2);
+    let len = args.arg(3);
+    let actual_out = args.arg(4);
+    system::sys_system_crashlog_read_impl(resource, offset, buffer, len, actual_out)
+}
+
+fn sys_kcounter_read(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let buffer = args.arg(1);
+    let buffer_size = args.arg(2);
+    let actual_out = args.arg(3);
+    let avail_out = args.arg(4);
+    kcounter::sys_kcounter_read_impl(resource, buffer, buffer_size, actual_out, avail_out)
+}
+
+fn sys_ktrace_read(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let data = args.arg(1);
+    let offset = args.arg(2) as u32;
+    let len = args.arg(3);
+    let actual = args.arg(4);
+    debug::sys_ktrace_read_impl(resource, data, offset, len, actual)
+}
+
+fn sys_ktrace_control(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let action = args.arg(1) as u32;
+    let options = args.arg(2) as u32;
+    let ptr = args.arg(3);
+    debug::sys_ktrace_control_impl(resource, action, options, ptr)
+}
+
+fn sys_ktrace_write(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let event_id = args.arg(1) as u32;
+    let arg0 = args.arg(2) as u32;
+    let arg1 = args.arg(3) as u32;
+    debug::sys_ktrace_write_impl(resource, event_id, arg0, arg1)
+}
+
+fn sys_profiler_control(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let action = args.arg(1) as u32;
+    let options = args.arg(2) as u32;
+    debug::sys_profiler_control_impl(resource, action, options)
+}
+
+fn sys_profiler_read(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let data = args.arg(1);
+    let offset = args.arg(2);
+    let len = args.arg(3);
+    let actual = args.arg(4);
+    debug::sys_profiler_read_impl(resource, data, offset, len, actual)
+}
+
+fn sys_perfmon_control(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let action = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    debug::sys_perfmon_control_impl(resource, action, buffer, buffer_size)
+}
+
+fn sys_perfmon_read(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let buffer = args.arg(1);
+    let buffer_size = args.arg(2);
+    let actual = args.arg(3);
+    debug::sys_perfmon_read_impl(resource, buffer, buffer_size, actual)
+}
+
+fn sys_itrace_control(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let action = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let buffer_size = args.arg(3);
+    debug::sys_itrace_control_impl(resource, action, buffer, buffer_size)
+}
+
+fn sys_itrace_get_buffer(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let index = args.arg(1) as u32;
+    let info = args.arg(2);
+    let vmo_out = args.arg(3);
+    debug::sys_itrace_get_buffer_impl(resource, index, info, vmo_out)
+}
+
+fn sys_efi_get_variable(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let variable = args.arg(1);
+    let data = args.arg(2);
+    let data_size = args.arg(3);
+    let actual = args.arg(4);
+    system::sys_efi_get_variable_impl(resource, variable, data, data_size, actual)
+}
+
+fn sys_efi_set_variable(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let variable = args.arg(1);
+    let data = args.arg(2);
+    let data_size = args.arg(3);
+    system::sys_efi_set_variable_impl(resource, variable, data, data_size)
+}
+
+fn sys_efi_get_time(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let time_out = args.arg(1);
+    system::sys_efi_get_time_impl(resource, time_out)
+}
+
+fn sys_debug_read(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let buffer = args.arg(1);
+    let buffer_size = args.arg(2);
+    let actual = args.arg(3);
+    debug::sys_debug_read_impl(resource, buffer, buffer_size, actual)
+}
+
+fn sys_socket_create(args: SyscallArgs) -> SyscallRet {
+    let options = args.arg(0) as u32;
+    let out0 = args.arg(1);
+    let out1 = args.arg(2);
+    socket::sys_socket_create_impl(options, out0, out1)
+}
+
+fn sys_socket_write(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let size = args.arg(3);
+    let actual = args.arg(4);
+    socket::sys_socket_write_impl(handle, options, buffer, size, actual)
+}
+
+fn sys_socket_read(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let buffer = args.arg(2);
+    let size = args.arg(3);
+    let actual = args.arg(4);
+    socket::sys_socket_read_impl(handle, options, buffer, size, actual)
+}
+
+fn sys_socket_shutdown(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    socket::sys_socket_shutdown_impl(handle, options)
+}
+
+fn sys_resource_create(args: SyscallArgs) -> SyscallRet {
+    let parent = args.arg(0) as u32;
+    let options = args.arg(1) as u32;
+    let base = args.arg(2) as u64;
+    let size = args.arg(3);
+    let name = args.arg(4);
+    let name_size = args.arg(5);
+    resource::sys_resource_create_impl(parent, options, base, size, name, name_size)
+}
+
+fn sys_system_get_event(args: SyscallArgs) -> SyscallRet {
+    let resource = args.arg(0) as u32;
+    let kind = args.arg(1) as u32;
+    system::sys_system_get_event_impl(resource, kind)
+}
+
+fn sys_system_get_version(args: SyscallArgs) -> SyscallRet {
+    let version_out = args.arg(0);
+    system::sys_system_get_version_impl(version_out)
+}
+
+fn sys_system_get_phys_mem(_args: SyscallArgs) -> SyscallRet {
+    system::sys_system_get_phys_mem_impl()
+}
+
+fn sys_system_get_features(args: SyscallArgs) -> SyscallRet {
+    let kind = args.arg(0) as u32;
+    let buffer = args.arg(1);
+    let buffer_size = args.arg(2);
+    system::sys_system_get_features_impl(kind, buffer, buffer_size)
+}
+
+fn sys_system_get_num_cpus(_args: SyscallArgs) -> SyscallRet {
+    system::sys_system_get_num_cpus_impl()
+}
+
 /// ============================================================================
 /// Architecture-Specific Entry Points
 /// ============================================================================
@@ -672,51 +1195,17 @@ pub extern "C" fn riscv_syscall_entry(
     syscall_dispatch(args)
 }
 
-/// ============================================================================
-/// Syscall Statistics
-/// ============================================================================
-
-/// Syscall statistics
-#[repr(C)]
-#[derive(Debug)]
-pub struct SyscallStats {
-    /// Total syscalls dispatched
-    pub total_calls: u64,
-
-    /// Syscalls by number
-    pub by_number: [u64; 256],
-}
-
-/// Global syscall statistics
-static mut SYSCALL_STATS: SyscallStats = SyscallStats {
-    total_calls: 0,
-    by_number: [0; 256],
-};
-
-/// Record a syscall invocation
-fn record_syscall(num: u32) {
-    unsafe {
-        SYSCALL_STATS.total_calls += 1;
-        if (num as usize) < 256 {
-            SYSCALL_STATS.by_number[num as usize] += 1;
-        }
-    }
-}
-
-/// Get syscall statistics
-pub fn get_syscall_stats() -> &'static SyscallStats {
-    unsafe { &SYSCALL_STATS }
-}
-
 /// ============================================================================
 /// Module Initialization
 /// ============================================================================
 
 /// Initialize the syscall subsystem
 pub fn init() {
+    stats::init();
+    crate::kernel::object::clock::init();
     log_info!("Syscall subsystem initialized");
     log_info!("  ABI version: 1 (stable)");
-    log_info!("  Syscalls defined: {}", 0x43); // Last syscall number
+    log_info!("  Syscalls defined: {:#x}", 0xF2); // Last syscall number
 }
 
 // ============================================================================
@@ -735,6 +1224,69 @@ mod tests {
 
         let unknown = SyscallNumber::from_raw(0xFFFF);
         assert_eq!(unknown, SyscallNumber::Unknown);
+
+        // Gaps between syscall groups are not valid numbers
+        assert_eq!(SyscallNumber::from_raw(0x09).name(), "rx_thread_get_affinity");
+        assert_eq!(SyscallNumber::from_raw(0x0A), SyscallNumber::Unknown);
+        assert_eq!(SyscallNumber::from_raw(0x18).name(), "rx_vmo_op_range");
+        assert_eq!(SyscallNumber::from_raw(0x19), SyscallNumber::Unknown);
+        assert_eq!(SyscallNumber::from_raw(0x49).name(), "rx_utc_reference_swap");
+        assert_eq!(SyscallNumber::from_raw(0x4A), SyscallNumber::Unknown);
+        assert_eq!(SyscallNumber::from_raw(0x28).name(), "rx_object_get_info");
+        assert_eq!(SyscallNumber::from_raw(0x2A).name(), "rx_channel_read_etc");
+        assert_eq!(SyscallNumber::from_raw(0x2B).name(), "rx_object_signal_peer");
+        assert_eq!(SyscallNumber::from_raw(0x2F).name(), "rx_counter_write");
+        assert_eq!(SyscallNumber::from_raw(0x33).name(), "rx_profile_create");
+        assert_eq!(SyscallNumber::from_raw(0x34).name(), "rx_object_set_profile");
+        assert_eq!(SyscallNumber::from_raw(0x35).name(), "rx_handle_close_many");
+        assert_eq!(SyscallNumber::from_raw(0x36).name(), "rx_object_get_child");
+        assert_eq!(SyscallNumber::from_raw(0x37), SyscallNumber::Unknown);
+        // Reserved in the ABI file but not implemented
+        assert_eq!(SyscallNumber::from_raw(0x70), SyscallNumber::Unknown);
+
+        assert_eq!(SyscallNumber::from_raw(0x50).name(), "rx_channel_call_etc");
+        assert_eq!(SyscallNumber::from_raw(0x90).name(), "rx_hypervisor_create");
+        assert_eq!(SyscallNumber::from_raw(0x94).name(), "rx_vcpu_enter");
+        assert_eq!(SyscallNumber::from_raw(0x98).name(), "rx_vcpu_write_state");
+        assert_eq!(SyscallNumber::from_raw(0x99), SyscallNumber::Unknown);
+
+        let bti_pin = SyscallNumber::from_raw(0xD3);
+        assert_eq!(bti_pin, SyscallNumber::rx_bti_pin);
+        assert_eq!(bti_pin.name(), "rx_bti_pin");
+        assert_eq!(SyscallNumber::from_raw(0xDA).name(), "rx_vmo_set_cache_policy");
+        assert_eq!(SyscallNumber::from_raw(0xDB).name(), "rx_vmo_transfer_data");
+        assert_eq!(SyscallNumber::from_raw(0xDC).name(), "rx_iommu_create");
+        assert_eq!(SyscallNumber::from_raw(0xDD).name(), "rx_bti_release_quarantine");
+        assert_eq!(SyscallNumber::from_raw(0xDE).name(), "rx_framebuffer_get_info");
+        assert_eq!(SyscallNumber::from_raw(0xDF).name(), "rx_ioports_request");
+
+        assert_eq!(SyscallNumber::from_raw(0xF1), SyscallNumber::rx_fifo_write);
+        assert_eq!(SyscallNumber::from_raw(0xF3), SyscallNumber::Unknown);
+
+        assert_eq!(SyscallNumber::from_raw(0xA3).name(), "rx_cprng_draw");
+        assert_eq!(SyscallNumber::from_raw(0xA0).name(), "rx_system_get_version");
+        assert_eq!(SyscallNumber::from_raw(0xA1).name(), "rx_system_get_phys_mem");
+        assert_eq!(SyscallNumber::from_raw(0xA2).name(), "rx_system_powerctl");
+        assert_eq!(SyscallNumber::from_raw(0xA5).name(), "rx_system_crashlog_read");
+        assert_eq!(SyscallNumber::from_raw(0xA6).name(), "rx_kcounter_read");
+        assert_eq!(SyscallNumber::from_raw(0xA7).name(), "rx_ktrace_read");
+        assert_eq!(SyscallNumber::from_raw(0xA9).name(), "rx_ktrace_write");
+        assert_eq!(SyscallNumber::from_raw(0xAA).name(), "rx_resource_create");
+        assert_eq!(SyscallNumber::from_raw(0xAB).name(), "rx_system_get_event");
+        assert_eq!(SyscallNumber::from_raw(0xAC).name(), "rx_system_get_features");
+        assert_eq!(SyscallNumber::from_raw(0xAD).name(), "rx_system_get_num_cpus");
+        assert_eq!(SyscallNumber::from_raw(0xAE).name(), "rx_profiler_control");
+        assert_eq!(SyscallNumber::from_raw(0xAF).name(), "rx_profiler_read");
+
+        assert_eq!(SyscallNumber::from_raw(0x82).name(), "rx_perfmon_control");
+        assert_eq!(SyscallNumber::from_raw(0x83).name(), "rx_perfmon_read");
+        assert_eq!(SyscallNumber::from_raw(0x84).name(), "rx_itrace_control");
+        assert_eq!(SyscallNumber::from_raw(0x85).name(), "rx_itrace_get_buffer");
+        assert_eq!(SyscallNumber::from_raw(0x86).name(), "rx_efi_get_variable");
+        assert_eq!(SyscallNumber::from_raw(0x87).name(), "rx_efi_set_variable");
+        assert_eq!(SyscallNumber::from_raw(0x88).name(), "rx_efi_get_time");
+        assert_eq!(SyscallNumber::from_raw(0x89).name(), "rx_debug_read");
+        assert_eq!(SyscallNumber::from_raw(0x8A), SyscallNumber::Unknown);
     }
 
     #[test]
@@ -749,6 +1301,7 @@ mod tests {
     #[test]
     fn test_ret_conversions() {
         assert_eq!(ok_to_ret(42), 42);
-        assert_eq!(err_to_ret(RX_ERR_NO_MEMORY), -(RX_ERR_NO_MEMORY as SyscallRet));
+        assert_eq!(err_to_ret(RX_ERR_NO_MEMORY), RX_ERR_NO_MEMORY as SyscallRet);
+        assert!(err_to_ret(RX_ERR_BAD_HANDLE) < 0);
     }
 }
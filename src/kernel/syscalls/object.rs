@@ -7,15 +7,14 @@
 //! Object System Calls
 //!
 //! This module implements object-related system calls for getting information,
-//! properties, and signaling kernel objects.
+//! and properties of kernel objects. Signaling lives with the event
+//! syscalls.
 //!
 //! # Syscalls Implemented
 //!
 //! - `rx_object_get_info` - Get information about a kernel object
 //! - `rx_object_get_property` - Get a property of a kernel object
 //! - `rx_object_set_property` - Set a property of a kernel object
-//! - `rx_object_signal` - Signal a kernel object
-//! - `rx_object_signal_peer` - Signal the peer of a kernel object
 //! - `rx_object_get_child` - Get a child object by koid
 //! - `rx_object_set_cookie` - Set a cookie on an object
 //! - `rx_object_get_cookie` - Get a cookie from an object
@@ -31,10 +30,20 @@
 use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::stats::{self, SyscallStatsRecord};
+use crate::kernel::syscalls::system::{validate_resource, ResourceKind};
+use crate::kernel::syscalls::{channel, counter, event, task};
+use crate::kernel::object::job;
+use crate::kernel::object::koid::{Koid, KOID_INVALID};
+use crate::kernel::numa::{self, NumaPolicy, NUMA_NODE_ANY};
+use crate::kernel::pmm;
+use crate::kernel::process;
+use crate::kernel::thread::{TaskRuntimeInfo, ThreadId};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 // Import logging macros
@@ -104,6 +113,21 @@ pub mod info_topic {
 
     /// Socket information
     pub const SOCKET: u32 = 0x13;
+
+    /// CPU and queue time of a thread, process or job
+    pub const TASK_RUNTIME: u32 = 0x15;
+
+    /// Per-syscall counts and latency (root resource only)
+    pub const SYSCALL_STATS: u32 = 0x14;
+
+    /// Every handle a process holds (needs `MANAGE` on the process)
+    pub const HANDLE_TABLE: u32 = 0x16;
+
+    /// Harvest and report the age of each committed page of a VMO or VMAR
+    pub const PAGE_AGES: u32 = 0x17;
+
+    /// CPUs and free memory of each NUMA node
+    pub const NUMA_NODES: u32 = 0x18;
 }
 
 /// ============================================================================
@@ -129,6 +153,15 @@ pub mod property {
 
     /// Job kill on OOM
     pub const JOB_KILL_ON_OOM: u32 = 0x05;
+
+    /// Job importance; the OOM killer kills the least important job first
+    pub const JOB_IMPORTANCE: u32 = 0x06;
+
+    /// NUMA placement of a VMO's pages (`NumaPolicy`, u32)
+    pub const VMO_NUMA_POLICY: u32 = 0x07;
+
+    /// NUMA node a thread prefers to run on (u32, `NUMA_NODE_ANY` for none)
+    pub const THREAD_HOME_NODE: u32 = 0x08;
 }
 
 /// ============================================================================
@@ -148,13 +181,70 @@ pub struct HandleBasicInfo {
     /// Object type
     pub type_: u32,
 
-    /// Related koid (for eventpairs, etc.)
+    /// Peer koid for channels and eventpairs, otherwise 0
     pub related_koid: u64,
 
     /// Object properties
     pub props: u32,
 }
 
+/// One handle in a process's handle table
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleTableRecord {
+    /// Handle value in the process
+    pub handle: u32,
+
+    /// Object type
+    pub type_: u32,
+
+    /// Kernel object ID
+    pub koid: u64,
+
+    /// Peer koid for channels and eventpairs, otherwise 0
+    pub related_koid: u64,
+
+    /// Rights associated with the handle
+    pub rights: u32,
+
+    /// Padding
+    _pad: u32,
+}
+
+/// Age of one page, from the `PAGE_AGES` topic
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PageAgeRecord {
+    /// Offset of the page in the VMO, or within the VMAR
+    pub offset: u64,
+
+    /// Harvests in a row, including this one, that found it unaccessed
+    pub age: u32,
+
+    /// `PAGE_AGE_DIRTY` if written since it was last cleaned
+    pub flags: u32,
+}
+
+/// `PageAgeRecord::flags` bit: the page is dirty
+pub const PAGE_AGE_DIRTY: u32 = 1 << 0;
+
+/// One NUMA node, from the `NUMA_NODES` topic
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumaNodeRecord {
+    /// Node number
+    pub node: u32,
+
+    /// Online CPUs on the node
+    pub cpu_count: u32,
+
+    /// Bytes of memory the PMM manages on the node
+    pub total_bytes: u64,
+
+    /// Of which free
+    pub free_bytes: u64,
+}
+
 /// Process information
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -193,8 +283,8 @@ pub struct ThreadInfo {
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct ThreadStats {
-    /// Total idle time
-    pub total_runtime: u64,
+    /// Time spent ready to run but waiting for a CPU
+    pub queue_time: u64,
 
     /// CPU time
     pub cpu_time: u64,
@@ -261,6 +351,22 @@ pub struct VmoInfo {
     _pad2: [u64; 3],
 }
 
+/// Build the `VmoInfo` record for a VMO
+fn vmo_info(vmo: &crate::kernel::object::vmo::Vmo) -> VmoInfo {
+    VmoInfo {
+        koid: vmo.id,
+        parent_koid: vmo.parent.lock().as_ref().map_or(0, |parent| parent.vmo),
+        num_children: vmo.children.lock().len() as u64,
+        num_mappings: vmo.mapping_count() as u64,
+        share_count: vmo.share_count() as u64,
+        flags: vmo.flags.0,
+        _pad: 0,
+        size_bytes: vmo.size() as u64,
+        committed_bytes: (vmo.pages.committed_count() * 4096) as u64,
+        _pad2: [0; 3],
+    }
+}
+
 /// VMAR information
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -464,6 +570,188 @@ fn single_record_result(
     Ok(())
 }
 
+/// Copy one `SyscallStatsRecord` per syscall to a user buffer
+///
+/// Writes as many records as fit; `avail_out` gets the total so the caller
+/// can retry with a larger buffer.
+fn syscall_stats_result(
+    buffer: usize,
+    buffer_size: usize,
+    actual_out: usize,
+    avail_out: usize,
+) -> Result {
+    let record_size = core::mem::size_of::<SyscallStatsRecord>();
+    let avail = stats::count();
+    let actual = avail.min(buffer_size / record_size);
+
+    for (index, record) in stats::snapshot().take(actual).enumerate() {
+        let user_ptr = UserPtr::<u8>::new(buffer + index * record_size);
+        unsafe {
+            copy_to_user(user_ptr, &record as *const SyscallStatsRecord as *const u8, record_size)?;
+        }
+    }
+
+    for (out, count) in [(actual_out, actual), (avail_out, avail)] {
+        if out != 0 {
+            let user_ptr = UserPtr::<u8>::new(out);
+            unsafe {
+                copy_to_user(user_ptr, &count as *const usize as *const u8, core::mem::size_of::<usize>())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the koid of the peer of a channel or eventpair handle, else 0
+///
+/// The object is found from the handle ID, as in the channel lookups.
+fn related_koid(handle: &Handle) -> Koid {
+    let related = match handle.obj_type() {
+        ObjectType::Channel => channel::peer_koid(handle.id),
+        ObjectType::EventPair => event::eventpair_koids(handle.id as u32).map(|(_, peer)| peer),
+        _ => None,
+    };
+    related.unwrap_or(KOID_INVALID)
+}
+
+/// Build the `HandleBasicInfo` record for a handle value
+///
+/// Eventpair and counter handle values are registry IDs rather than table
+/// slots; they report their type's default rights.
+fn handle_basic_info(table: &HandleTable, handle_val: u32) -> Result<HandleBasicInfo> {
+    let (koid, rights, type_, related_koid) = if let Some(handle) = table.get(handle_val) {
+        (handle.koid(), handle.rights, handle.obj_type(), related_koid(&handle))
+    } else if let Some((koid, peer)) = event::eventpair_koids(handle_val) {
+        let type_ = ObjectType::EventPair;
+        (koid, Rights::default_for_type(type_), type_, peer)
+    } else if let Some(koid) = counter::counter_koid(handle_val) {
+        let type_ = ObjectType::Counter;
+        (koid, Rights::default_for_type(type_), type_, KOID_INVALID)
+    } else {
+        return Err(RX_ERR_BAD_HANDLE);
+    };
+
+    Ok(HandleBasicInfo {
+        koid,
+        rights: rights.into_raw(),
+        type_: type_.into_raw(),
+        related_koid,
+        props: 0,
+    })
+}
+
+/// Build the `HandleTableRecord` for one handle
+fn handle_table_record(handle_val: u32, handle: &Handle) -> HandleTableRecord {
+    HandleTableRecord {
+        handle: handle_val,
+        type_: handle.obj_type().into_raw(),
+        koid: handle.koid(),
+        related_koid: related_koid(handle),
+        rights: handle.rights.into_raw(),
+        _pad: 0,
+    }
+}
+
+/// List the handles of the process `handle_val` refers to
+///
+/// `handle_val` must be a process handle with `MANAGE`. Every thread
+/// shares one handle table for now, so the records come from `table`.
+fn handle_table_records(table: &HandleTable, handle_val: u32) -> Result<Vec<HandleTableRecord>> {
+    let handle = table.get(handle_val).ok_or(RX_ERR_BAD_HANDLE)?;
+    if handle.obj_type() != ObjectType::Process {
+        return Err(RX_ERR_WRONG_TYPE);
+    }
+    handle.require(Rights::MANAGE)?;
+
+    Ok(table_records(table))
+}
+
+/// Build the `HandleTableRecord` for every handle in `table`
+pub(crate) fn table_records(table: &HandleTable) -> Vec<HandleTableRecord> {
+    table
+        .handle_values()
+        .into_iter()
+        .filter_map(|value| table.get(value).map(|h| handle_table_record(value, &h)))
+        .collect()
+}
+
+/// Harvest the page ages of the VMO or VMAR `handle_val` names
+fn page_age_records(handle_val: u32) -> Result<Vec<PageAgeRecord>> {
+    let is_vmar = crate::kernel::thread::current_thread_handle_table()
+        .get(handle_val)
+        .map_or(false, |handle| handle.obj_type() == ObjectType::Vmar);
+
+    let ages: Vec<(u64, crate::kernel::pmm::PageAge)> = if is_vmar {
+        let vmar = super::vmar::lookup_vmar(handle_val)?;
+        vmar.harvest(0, vmar.size)?
+    } else {
+        let (vmo, _handle) = super::vmo::lookup_vmo_from_handle(handle_val, Rights::READ)?;
+        vmo.harvest(0, vmo.size())?
+            .into_iter()
+            .map(|(offset, age)| (offset as u64, age))
+            .collect()
+    };
+
+    Ok(ages
+        .into_iter()
+        .map(|(offset, age)| PageAgeRecord {
+            offset,
+            age: age.age as u32,
+            flags: if age.dirty { PAGE_AGE_DIRTY } else { 0 },
+        })
+        .collect())
+}
+
+/// A record for every NUMA node
+fn numa_node_records() -> Vec<NumaNodeRecord> {
+    (0..numa::node_count())
+        .map(|node| {
+            let (free, total) = pmm::pmm_count_node_pages(node);
+            NumaNodeRecord {
+                node,
+                cpu_count: numa::node_cpu_mask(node).count_ones(),
+                total_bytes: total * pmm::PAGE_SIZE as u64,
+                free_bytes: free * pmm::PAGE_SIZE as u64,
+            }
+        })
+        .collect()
+}
+
+/// Copy as many records as fit to a user buffer
+///
+/// `avail_out` gets the total so the caller can retry with a larger
+/// buffer.
+fn records_result<T>(
+    records: &[T],
+    buffer: usize,
+    buffer_size: usize,
+    actual_out: usize,
+    avail_out: usize,
+) -> Result {
+    let record_size = core::mem::size_of::<T>();
+    let avail = records.len();
+    let actual = avail.min(buffer_size / record_size);
+
+    if actual > 0 {
+        let user_ptr = UserPtr::<u8>::new(buffer);
+        unsafe {
+            copy_to_user(user_ptr, records.as_ptr() as *const u8, actual * record_size)?;
+        }
+    }
+
+    for (out, count) in [(actual_out, actual), (avail_out, avail)] {
+        if out != 0 {
+            let user_ptr = UserPtr::<u8>::new(out);
+            unsafe {
+                copy_to_user(user_ptr, &count as *const usize as *const u8, core::mem::size_of::<usize>())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// ============================================================================
 /// Syscall: Object Get Info
 /// ============================================================================
@@ -504,13 +792,10 @@ pub fn sys_object_get_info_impl(
         }
 
         info_topic::HANDLE_BASIC => {
-            // TODO: Implement proper handle lookup
-            let info = HandleBasicInfo {
-                koid: handle_val as u64,
-                rights: 0xFFFF, // TODO: get actual rights
-                type_: 0,       // TODO: get actual type
-                related_koid: 0,
-                props: 0,
+            let table = crate::kernel::thread::current_thread_handle_table();
+            let info = match handle_basic_info(table, handle_val) {
+                Ok(info) => info,
+                Err(err) => return err_to_ret(err),
             };
 
             match single_record_result(
@@ -605,11 +890,16 @@ pub fn sys_object_get_info_impl(
         }
 
         info_topic::THREAD_STATS => {
-            // TODO: Implement proper thread stats
+            let thread = match task::lookup_thread(handle_val as ThreadId) {
+                Some(thread) => thread,
+                None => return err_to_ret(RX_ERR_BAD_HANDLE),
+            };
+
+            let runtime = thread.runtime();
             let info = ThreadStats {
-                total_runtime: 0,
-                cpu_time: 0,
-                context_switches: 0,
+                queue_time: runtime.queue_time,
+                cpu_time: runtime.cpu_time,
+                context_switches: runtime.context_switches,
                 page_faults: 0,
                 _pad: [0; 4],
             };
@@ -650,18 +940,9 @@ pub fn sys_object_get_info_impl(
         }
 
         info_topic::VMO => {
-            // TODO: Implement proper VMO lookup
-            let info = VmoInfo {
-                koid: handle_val as u64,
-                parent_koid: 0,
-                num_children: 0,
-                num_mappings: 0,
-                share_count: 0,
-                flags: 0,
-                _pad: 0,
-                size_bytes: 0,
-                committed_bytes: 0,
-                _pad2: [0; 3],
+            let info = match super::vmo::lookup_vmo_from_handle(handle_val, Rights::READ) {
+                Ok((vmo, _handle)) => vmo_info(&vmo),
+                Err(err) => return err_to_ret(err),
             };
 
             match single_record_result(
@@ -821,6 +1102,84 @@ pub fn sys_object_get_info_impl(
             }
         }
 
+        info_topic::TASK_RUNTIME => {
+            // Handles are task IDs; try a process, then a thread, then a
+            // job, as rx_task_kill does
+            let id = handle_val as u64;
+            let info = if let Some(process) = process::lookup(id) {
+                process.runtime()
+            } else if let Some(thread) = task::lookup_thread(id) {
+                thread.runtime()
+            } else if let Some(job) = job::lookup(id) {
+                job.runtime()
+            } else {
+                return err_to_ret(RX_ERR_BAD_HANDLE);
+            };
+
+            match single_record_result(
+                buffer,
+                buffer_size,
+                actual_out,
+                avail_out,
+                &info as *const TaskRuntimeInfo as *const u8,
+                core::mem::size_of::<TaskRuntimeInfo>(),
+            ) {
+                Ok(()) => ok_to_ret(0),
+                Err(err) => err_to_ret(err),
+            }
+        }
+
+        info_topic::SYSCALL_STATS => {
+            if let Err(err) = validate_resource(handle_val, ResourceKind::Root) {
+                log_error!("sys_object_get_info: invalid resource: {:?}", err);
+                return err_to_ret(err);
+            }
+
+            match syscall_stats_result(buffer, buffer_size, actual_out, avail_out) {
+                Ok(()) => ok_to_ret(0),
+                Err(err) => err_to_ret(err),
+            }
+        }
+
+        info_topic::HANDLE_TABLE => {
+            let table = crate::kernel::thread::current_thread_handle_table();
+            let records = match handle_table_records(table, handle_val) {
+                Ok(records) => records,
+                Err(err) => {
+                    log_error!("sys_object_get_info: cannot inspect handles: {:?}", err);
+                    return err_to_ret(err);
+                }
+            };
+
+            match records_result(&records, buffer, buffer_size, actual_out, avail_out) {
+                Ok(()) => ok_to_ret(0),
+                Err(err) => err_to_ret(err),
+            }
+        }
+
+        info_topic::PAGE_AGES => {
+            let records = match page_age_records(handle_val) {
+                Ok(records) => records,
+                Err(err) => {
+                    log_error!("sys_object_get_info: cannot harvest page ages: {:?}", err);
+                    return err_to_ret(err);
+                }
+            };
+
+            match records_result(&records, buffer, buffer_size, actual_out, avail_out) {
+                Ok(()) => ok_to_ret(0),
+                Err(err) => err_to_ret(err),
+            }
+        }
+
+        info_topic::NUMA_NODES => {
+            let records = numa_node_records();
+            match records_result(&records, buffer, buffer_size, actual_out, avail_out) {
+                Ok(()) => ok_to_ret(0),
+                Err(err) => err_to_ret(err),
+            }
+        }
+
         _ => {
             log_error!("sys_object_get_info: unsupported topic {:#x}", topic);
             err_to_ret(RX_ERR_NOT_SUPPORTED)
@@ -952,6 +1311,46 @@ pub fn sys_object_get_property_impl(
             ok_to_ret(0)
         }
 
+        property::JOB_KILL_ON_OOM => {
+            if size < core::mem::size_of::<u64>() {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
+
+            let job = match job::lookup(handle_val as job::JobId) {
+                Some(job) => job,
+                None => return err_to_ret(RX_ERR_BAD_HANDLE),
+            };
+            let kill_on_oom = job.kill_on_oom() as u64;
+            let user_ptr = UserPtr::<u8>::new(value);
+            unsafe {
+                if let Err(err) = copy_to_user(user_ptr, &kill_on_oom as *const u64 as *const u8, 8) {
+                    return err_to_ret(err.into());
+                }
+            }
+
+            ok_to_ret(0)
+        }
+
+        property::JOB_IMPORTANCE => {
+            if size < core::mem::size_of::<u32>() {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
+
+            let job = match job::lookup(handle_val as job::JobId) {
+                Some(job) => job,
+                None => return err_to_ret(RX_ERR_BAD_HANDLE),
+            };
+            let importance = job.importance();
+            let user_ptr = UserPtr::<u8>::new(value);
+            unsafe {
+                if let Err(err) = copy_to_user(user_ptr, &importance as *const u32 as *const u8, 4) {
+                    return err_to_ret(err.into());
+                }
+            }
+
+            ok_to_ret(0)
+        }
+
         _ => {
             log_error!("sys_object_get_property: unsupported property {:#x}", property);
             err_to_ret(RX_ERR_INVALID_ARGS)
@@ -1092,96 +1491,150 @@ pub fn sys_object_set_property_impl(
                 return err_to_ret(RX_ERR_INVALID_ARGS);
             }
 
-            // TODO: Set actual kill-on-oom flag on job
+            let job = match job::lookup(handle_val as job::JobId) {
+                Some(job) => job,
+                None => return err_to_ret(RX_ERR_BAD_HANDLE),
+            };
+            job.set_kill_on_oom(kill_on_oom == 1);
             log_debug!(
-                "sys_object_set_property: set kill-on-oom to {}",
-                kill_on_oom == 1
+                "sys_object_set_property: job {} kill-on-oom {}",
+                job.id, kill_on_oom == 1
             );
 
             ok_to_ret(0)
         }
 
-        _ => {
-            log_error!("sys_object_set_property: unsupported property {:#x}", property);
-            err_to_ret(RX_ERR_INVALID_ARGS)
+        property::JOB_IMPORTANCE => {
+            if size < core::mem::size_of::<u32>() {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
+
+            let mut importance = 0u32;
+            let user_ptr = UserPtr::<u8>::new(value);
+            unsafe {
+                if let Err(err) = copy_from_user(&mut importance as *mut u32 as *mut u8, user_ptr, 4) {
+                    return err_to_ret(err.into());
+                }
+            }
+
+            let job = match job::lookup(handle_val as job::JobId) {
+                Some(job) => job,
+                None => return err_to_ret(RX_ERR_BAD_HANDLE),
+            };
+            job.set_importance(importance);
+            log_debug!("sys_object_set_property: job {} importance {}", job.id, importance);
+
+            ok_to_ret(0)
         }
-    }
-}
 
-/// ============================================================================
-/// Syscall: Object Signal
-/// ============================================================================
+        property::VMO_NUMA_POLICY => {
+            if size < core::mem::size_of::<u32>() {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
 
-/// Signal a kernel object syscall handler
-///
-/// # Arguments
-///
-/// * `handle_val` - Handle value
-/// * `clear_mask` - Signal bits to clear
-/// * `set_mask` - Signal bits to set
-///
-/// # Returns
-///
-/// * On success: 0
-/// * On error: Negative error code
-pub fn sys_object_signal_impl(handle_val: u32, clear_mask: u32, set_mask: u32) -> SyscallRet {
-    log_debug!(
-        "sys_object_signal: handle={:#x} clear={:#x} set={:#x}",
-        handle_val, clear_mask, set_mask
-    );
+            let mut raw = 0u32;
+            let user_ptr = UserPtr::<u8>::new(value);
+            unsafe {
+                if let Err(err) = copy_from_user(&mut raw as *mut u32 as *mut u8, user_ptr, 4) {
+                    return err_to_ret(err.into());
+                }
+            }
 
-    // TODO: Implement proper handle lookup and signaling
-    // For now, just log
-    log_info!("Object signal: handle={:#x} clear={:#x} set={:#x}", handle_val, clear_mask, set_mask);
+            let policy = match NumaPolicy::try_from_raw(raw) {
+                Some(policy) => policy,
+                None => return err_to_ret(RX_ERR_INVALID_ARGS),
+            };
+            let vmo = match super::vmo::lookup_vmo_from_handle(handle_val, Rights::WRITE) {
+                Ok((vmo, _handle)) => vmo,
+                Err(err) => return err_to_ret(err),
+            };
+            vmo.set_numa_policy(policy);
+            log_debug!("sys_object_set_property: vmo numa policy {:?}", policy);
 
-    ok_to_ret(0)
+            ok_to_ret(0)
+        }
+
+        property::THREAD_HOME_NODE => {
+            if size < core::mem::size_of::<u32>() {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
+
+            let mut node = 0u32;
+            let user_ptr = UserPtr::<u8>::new(value);
+            unsafe {
+                if let Err(err) = copy_from_user(&mut node as *mut u32 as *mut u8, user_ptr, 4) {
+                    return err_to_ret(err.into());
+                }
+            }
+
+            if node != NUMA_NODE_ANY && node >= numa::node_count() {
+                return err_to_ret(RX_ERR_OUT_OF_RANGE);
+            }
+            let thread = match task::lookup_thread(handle_val as ThreadId) {
+                Some(thread) => thread,
+                None => return err_to_ret(RX_ERR_BAD_HANDLE),
+            };
+            thread.set_home_node(node);
+            log_debug!("sys_object_set_property: thread {} home node {:#x}", thread.tid, node);
+
+            ok_to_ret(0)
+        }
+
+        _ => {
+            log_error!("sys_object_set_property: unsupported property {:#x}", property);
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        }
+    }
 }
 
 /// ============================================================================
-/// Syscall: Object Signal Peer
+/// Syscall: Object Get Child
 /// ============================================================================
 
-/// Signal the peer of a kernel object syscall handler
+/// Find the task named `koid` below `parent`
 ///
-/// # Arguments
-///
-/// * `handle_val` - Handle value
-/// * `clear_mask` - Signal bits to clear
-/// * `set_mask` - Signal bits to set
+/// The root job sees every job, process and thread in the system, so a
+/// debugger holding it can resolve any task koid. Other jobs only see
+/// their direct child jobs and processes.
 ///
 /// # Returns
 ///
-/// * On success: 0
-/// * On error: Negative error code
-pub fn sys_object_signal_peer_impl(
-    handle_val: u32,
-    clear_mask: u32,
-    set_mask: u32,
-) -> SyscallRet {
-    log_debug!(
-        "sys_object_signal_peer: handle={:#x} clear={:#x} set={:#x}",
-        handle_val, clear_mask, set_mask
-    );
-
-    // TODO: Implement proper handle lookup and peer signaling
-    // For now, just log
-    log_info!(
-        "Object signal peer: handle={:#x} clear={:#x} set={:#x}",
-        handle_val, clear_mask, set_mask
-    );
+/// The task's ID, which is its handle value
+fn find_child(parent: &job::Job, koid: Koid) -> Option<u64> {
+    if parent.id == job::JOB_ID_ROOT {
+        return job::lookup_by_koid(koid)
+            .map(|job| job.id)
+            .or_else(|| process::lookup_by_koid(koid).map(|p| p.pid))
+            .or_else(|| crate::kernel::thread::get_thread_by_koid(koid).map(|t| t.tid));
+    }
 
-    ok_to_ret(0)
+    let child_job = parent
+        .children
+        .lock()
+        .iter()
+        .filter_map(|&id| job::lookup(id))
+        .find(|job| job.koid == koid)
+        .map(|job| job.id);
+
+    child_job.or_else(|| {
+        parent
+            .processes
+            .lock()
+            .iter()
+            .filter_map(|&pid| process::lookup(pid as process::ProcessId))
+            .find(|p| p.koid == koid)
+            .map(|p| p.pid)
+    })
 }
 
-/// ============================================================================
-/// Syscall: Object Get Child
-/// ============================================================================
-
 /// Get a child object by koid syscall handler
 ///
+/// Task handles are task IDs and carry no rights yet, so `rights` is
+/// accepted but not applied.
+///
 /// # Arguments
 ///
-/// * `handle_val` - Parent handle value
+/// * `handle_val` - Parent job handle value (0 means the root job)
 /// * `koid` - Kernel object ID of child
 /// * `rights` - Rights for the new handle
 ///
@@ -1189,21 +1642,32 @@ pub fn sys_object_signal_peer_impl(
 ///
 /// * On success: Child handle value
 /// * On error: Negative error code
+///   - `RX_ERR_BAD_HANDLE` if `handle_val` is not a job
+///   - `RX_ERR_NOT_FOUND` if no child has that koid
 pub fn sys_object_get_child_impl(handle_val: u32, koid: u64, rights: u32) -> SyscallRet {
     log_debug!(
         "sys_object_get_child: handle={:#x} koid={} rights={:#x}",
         handle_val, koid, rights
     );
 
-    // TODO: Implement proper handle lookup and child enumeration
-    // For now, just log
-    log_info!(
-        "Object get child: handle={:#x} koid={} rights={:#x}",
-        handle_val, koid, rights
-    );
+    let job_id = if handle_val == 0 {
+        job::JOB_ID_ROOT
+    } else {
+        handle_val as job::JobId
+    };
 
-    // Return a placeholder handle
-    ok_to_ret((koid & 0xFFFFFFFF) as usize)
+    let parent = match job::lookup(job_id) {
+        Some(job) => job,
+        None => return err_to_ret(RX_ERR_BAD_HANDLE),
+    };
+
+    match find_child(&parent, koid) {
+        Some(id) => ok_to_ret(id as usize),
+        None => {
+            log_debug!("sys_object_get_child: koid {} not found", koid);
+            err_to_ret(RX_ERR_NOT_FOUND)
+        }
+    }
 }
 
 /// ============================================================================
@@ -1338,6 +1802,73 @@ mod tests {
         assert_eq!(info_topic::HANDLE_VALID, 0x00);
         assert_eq!(info_topic::HANDLE_BASIC, 0x01);
         assert_eq!(info_topic::PROCESS, 0x02);
+        assert_eq!(info_topic::SYSCALL_STATS, 0x14);
+        assert_eq!(info_topic::TASK_RUNTIME, 0x15);
+        assert_eq!(info_topic::HANDLE_TABLE, 0x16);
+        assert_eq!(info_topic::PAGE_AGES, 0x17);
+        assert_eq!(info_topic::NUMA_NODES, 0x18);
+    }
+
+    #[test]
+    fn test_numa_node_records() {
+        let records = numa_node_records();
+        assert_eq!(records.len(), numa::node_count() as usize);
+        assert_eq!(records[0].node, 0);
+        for record in &records {
+            assert!(record.free_bytes <= record.total_bytes);
+        }
+    }
+
+    #[test]
+    fn test_handle_table_records() {
+        let process = KernelObjectBase::new(ObjectType::Process);
+        let timer = KernelObjectBase::new(ObjectType::Timer);
+        let table = HandleTable::new();
+
+        let inspector = table.add(Handle::new(&process, Rights::MANAGE)).unwrap();
+        let target = table.add(Handle::new(&timer, Rights::SIGNAL)).unwrap();
+
+        let records = handle_table_records(&table, inspector).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].handle, target);
+        assert_eq!(records[1].type_, ObjectType::Timer.into_raw());
+        assert_eq!(records[1].koid, timer.koid);
+        assert_eq!(records[1].related_koid, 0);
+        assert_eq!(records[1].rights, Rights::SIGNAL.into_raw());
+    }
+
+    #[test]
+    fn test_handle_basic_info() {
+        let timer = KernelObjectBase::new(ObjectType::Timer);
+        let table = HandleTable::new();
+        let value = table.add(Handle::new(&timer, Rights::SIGNAL)).unwrap();
+
+        let info = handle_basic_info(&table, value).unwrap();
+        assert_eq!(info.koid, timer.koid);
+        assert_eq!(info.type_, ObjectType::Timer.into_raw());
+        assert_eq!(info.rights, Rights::SIGNAL.into_raw());
+        assert_eq!(info.related_koid, KOID_INVALID);
+
+        let counter = counter::sys_counter_create_impl(0) as u32;
+        let info = handle_basic_info(&table, counter).unwrap();
+        assert_eq!(Some(info.koid), counter::counter_koid(counter));
+        assert_eq!(info.type_, ObjectType::Counter.into_raw());
+
+        assert_eq!(handle_basic_info(&table, 0xdead_beef).err(), Some(RX_ERR_BAD_HANDLE));
+    }
+
+    #[test]
+    fn test_handle_table_records_checks_handle() {
+        let process = KernelObjectBase::new(ObjectType::Process);
+        let timer = KernelObjectBase::new(ObjectType::Timer);
+        let table = HandleTable::new();
+
+        let no_manage = table.add(Handle::new(&process, Rights::READ)).unwrap();
+        let not_process = table.add(Handle::new(&timer, Rights::MANAGE)).unwrap();
+
+        assert_eq!(handle_table_records(&table, no_manage), Err(RX_ERR_ACCESS_DENIED));
+        assert_eq!(handle_table_records(&table, not_process), Err(RX_ERR_WRONG_TYPE));
+        assert_eq!(handle_table_records(&table, 200), Err(RX_ERR_BAD_HANDLE));
     }
 
     #[test]
@@ -1356,6 +1887,12 @@ mod tests {
         assert!(core::mem::size_of::<ProcessInfo>() >= 24);
     }
 
+    #[test]
+    fn test_page_age_record_size() {
+        // Part of the ABI: offset, age and flags with no padding
+        assert_eq!(core::mem::size_of::<PageAgeRecord>(), 16);
+    }
+
     #[test]
     fn test_single_record_result_buffer_too_small() {
         let result = single_record_result(
@@ -1384,4 +1921,19 @@ mod tests {
         // This will fail to copy to user address 0x1000, but we can test the size logic
         assert!(result.is_ok() || result.unwrap_err() != RX_ERR_BUFFER_TOO_SMALL);
     }
+
+    #[test]
+    fn test_vmo_info() {
+        use crate::kernel::object::vmo::{Vmo, VmoFlags};
+
+        let vmo = Vmo::create(0x3000, VmoFlags::RESIZABLE).unwrap();
+        vmo.pages.insert(1, 0x2000_1000);
+
+        let info = vmo_info(&vmo);
+        assert_eq!(info.koid, vmo.id);
+        assert_eq!(info.size_bytes, 0x3000);
+        assert_eq!(info.committed_bytes, 0x1000);
+        assert_eq!(info.flags, VmoFlags::RESIZABLE.0);
+        assert_eq!(info.parent_koid, 0);
+    }
 }
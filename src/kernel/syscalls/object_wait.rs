@@ -30,6 +30,7 @@ use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use crate::kernel::sync::Mutex;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
@@ -86,6 +87,12 @@ pub mod wait_options {
 
 /// Signal masks
 pub mod signal {
+    /// Eventpair: the other side has lost its last handle
+    pub const PEER_CLOSED: u64 = 0x00000004;
+
+    /// VMO: reclaim discarded its pages while it was unlocked
+    pub const VMO_DISCARDED: u64 = 0x00000010;
+
     /// Handle has been closed
     pub const HANDLE_CLOSED: u64 = 0x00800000;
 
@@ -183,30 +190,117 @@ impl Default for WaitStateObserver {
 }
 
 /// ============================================================================
-/// Wait Queue
+/// Wait Node
 /// ============================================================================
 
-/// Wait queue entry
+/// One object a wait node waits on
+struct WaitNodeItem {
+    /// Handle being waited on
+    handle: u32,
+
+    /// Signals being waited for
+    signals: u64,
+
+    /// Signals seen on the handle while waiting
+    observed: AtomicU64,
+}
+
+/// Wait node
 ///
-/// Tracks a thread that is waiting on an object.
-struct WaitQueueEntry {
+/// A thread's pending wait on one or more objects. The node is queued on
+/// the wait queue of every handle it waits on; whichever queue signals or
+/// cancels it first wakes the thread, which then dequeues the node from
+/// all of them.
+pub struct WaitNode {
     /// Thread ID of the waiting thread
     thread_id: usize,
 
-    /// Signals being waited for
-    signals: u64,
+    /// Objects waited on, in wait item order
+    items: Vec<WaitNodeItem>,
 
-    /// Deadline for timeout (0 = no deadline)
-    deadline: u64,
+    /// A wanted signal arrived or a handle was closed
+    woken: AtomicBool,
 
-    /// User pointer to write observed signals
-    observed_out: usize,
+    /// A waited handle was closed
+    canceled: AtomicBool,
+}
 
-    /// Whether this entry has been signaled
-    signaled: AtomicBool,
+impl WaitNode {
+    /// Create a node waiting on each `(handle, signals)` pair
+    fn new(thread_id: usize, items: impl Iterator<Item = (u32, u64)>) -> Arc<Self> {
+        Arc::new(Self {
+            thread_id,
+            items: items
+                .map(|(handle, signals)| WaitNodeItem {
+                    handle,
+                    signals,
+                    observed: AtomicU64::new(0),
+                })
+                .collect(),
+            woken: AtomicBool::new(false),
+            canceled: AtomicBool::new(false),
+        })
+    }
 
-    /// Observed signals
-    observed: AtomicU64,
+    /// Check if the node has been woken
+    pub fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Acquire)
+    }
+
+    /// Check if a waited handle was closed
+    pub fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::Acquire)
+    }
+
+    /// Get the signals seen on item `index` while waiting
+    pub fn observed(&self, index: usize) -> u64 {
+        self.items[index].observed.load(Ordering::Acquire)
+    }
+
+    /// Record `signals` on item `index`
+    ///
+    /// Wakes the thread if the item waits for any of them. Returns true
+    /// if it did.
+    fn signal(&self, index: usize, signals: u64) -> bool {
+        let item = &self.items[index];
+        if item.signals & signals == 0 {
+            return false;
+        }
+        item.observed.fetch_or(signals, Ordering::AcqRel);
+        self.wake();
+        true
+    }
+
+    /// Record that item `index`'s handle was closed and wake the thread
+    fn cancel(&self, index: usize) {
+        self.items[index].observed.fetch_or(signal::HANDLE_CLOSED, Ordering::AcqRel);
+        self.canceled.store(true, Ordering::Release);
+        self.wake();
+    }
+
+    /// Mark the node woken; only the first caller wakes the thread
+    fn wake(&self) {
+        if !self.woken.swap(true, Ordering::AcqRel) {
+            use crate::kernel::thread;
+            let _ = thread::wake_thread(self.thread_id as u64);
+            log_debug!("WaitNode: Woke thread {}", self.thread_id);
+        }
+    }
+}
+
+/// ============================================================================
+/// Wait Queue
+/// ============================================================================
+
+/// Wait queue entry
+///
+/// One item of a wait node, queued on that item's handle.
+struct WaitQueueEntry {
+    /// Node of the waiting thread
+    node: Arc<WaitNode>,
+
+    /// Index of the item this entry is for
+    index: usize,
 }
 
 /// Wait queue for an object
@@ -214,7 +308,7 @@ struct WaitQueueEntry {
 /// Tracks all threads waiting on a particular object.
 pub struct WaitQueue {
     /// Entries in the wait queue
-    entries: Mutex<alloc::vec::Vec<WaitQueueEntry>>,
+    entries: Mutex<Vec<WaitQueueEntry>>,
 
     /// Number of waiters
     count: AtomicUsize,
@@ -224,7 +318,7 @@ impl WaitQueue {
     /// Create a new wait queue
     pub const fn new() -> Self {
         Self {
-            entries: Mutex::new(alloc::vec::Vec::new()),
+            entries: Mutex::new(Vec::new()),
             count: AtomicUsize::new(0),
         }
     }
@@ -237,19 +331,8 @@ impl WaitQueue {
         _deadline: u64,
         _observed_out: usize,
     ) -> Result {
-        let mut entries = self.entries.lock();
-
-        let entry = WaitQueueEntry {
-            thread_id,
-            signals,
-            deadline: 0,
-            observed_out: 0,
-            signaled: AtomicBool::new(false),
-            observed: AtomicU64::new(0),
-        };
-
-        entries.push(entry);
-        self.count.fetch_add(1, Ordering::Relaxed);
+        let node = WaitNode::new(thread_id, core::iter::once((0, signals)));
+        self.add(&node, 0);
 
         // Block the thread using the thread module
         // Note: This requires the thread to be registered in the thread registry
@@ -261,73 +344,51 @@ impl WaitQueue {
         Ok(())
     }
 
+    /// Queue item `index` of `node`
+    fn add(&self, node: &Arc<WaitNode>, index: usize) {
+        self.entries.lock().push(WaitQueueEntry { node: node.clone(), index });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Dequeue every entry of `node`
+    fn remove(&self, node: &Arc<WaitNode>) {
+        let mut entries = self.entries.lock();
+        let original_len = entries.len();
+        entries.retain(|e| !Arc::ptr_eq(&e.node, node));
+        self.count.fetch_sub(original_len - entries.len(), Ordering::Relaxed);
+    }
+
     /// Signal waiters matching the signal mask and wake them up
+    ///
+    /// Woken entries leave the queue; their threads dequeue the rest of
+    /// their nodes themselves.
     pub fn signal(&self, signals: u64) -> usize {
         let mut entries = self.entries.lock();
-        let mut signaled_count = 0;
-
-        // Collect thread IDs to wake
-        let mut threads_to_wake: alloc::vec::Vec<usize> = alloc::vec::Vec::new();
-
-        for entry in entries.iter() {
-            // Check if this waiter is interested in these signals
-            if entry.signals & signals != 0 {
-                // Set the observed signals
-                entry.observed.store(signals, Ordering::Release);
-                // Mark as signaled
-                entry.signaled.store(true, Ordering::Release);
-
-                // Track thread to wake
-                threads_to_wake.push(entry.thread_id);
-
-                signaled_count += 1;
-            }
-        }
-
-        // Remove all signaled entries
         let original_len = entries.len();
-        entries.retain(|e| !e.signaled.load(Ordering::Acquire));
-        let removed = original_len - entries.len();
-        self.count.fetch_sub(removed, Ordering::Relaxed);
+        entries.retain(|e| !e.node.signal(e.index, signals));
+        let woken = original_len - entries.len();
+        self.count.fetch_sub(woken, Ordering::Relaxed);
 
-        // Wake up all the threads outside the lock
-        drop(entries);
-        for thread_id in threads_to_wake {
-            use crate::kernel::thread;
-            let _ = thread::wake_thread(thread_id as u64);
-            log_debug!("WaitQueue: Woke thread {} with signals {:#x}", thread_id, signals);
+        if woken > 0 {
+            log_debug!("WaitQueue: Woke {} waiters with signals {:#x}", woken, signals);
         }
-
-        signaled_count
+        woken
     }
 
-    /// Wake up all waiters with a timeout error
-    pub fn timeout_all(&self) -> usize {
+    /// Wake up all waiters because the handle was closed
+    ///
+    /// Their waits fail with `RX_ERR_CANCELED` and report `HANDLE_CLOSED`
+    /// for this handle.
+    pub fn cancel_all(&self) -> usize {
         let mut entries = self.entries.lock();
         let count = entries.len();
 
-        // Collect thread IDs to wake
-        let mut threads_to_wake: alloc::vec::Vec<usize> = alloc::vec::Vec::new();
-
-        // Signal all entries with a timeout
-        for entry in entries.iter() {
-            entry.observed.store(signal::HANDLE_CLOSED, Ordering::Release);
-            entry.signaled.store(true, Ordering::Release);
-            threads_to_wake.push(entry.thread_id);
-            log_debug!("WaitQueue: Timing out thread {}", entry.thread_id);
+        for entry in entries.drain(..) {
+            log_debug!("WaitQueue: Canceling thread {}", entry.node.thread_id);
+            entry.node.cancel(entry.index);
         }
-
-        // Clear all entries
-        entries.clear();
         self.count.store(0, Ordering::Relaxed);
 
-        // Wake up all the threads outside the lock
-        drop(entries);
-        for thread_id in threads_to_wake {
-            use crate::kernel::thread;
-            let _ = thread::wake_thread(thread_id as u64);
-        }
-
         count
     }
 
@@ -344,6 +405,23 @@ pub fn wake_waiters(handle: u32, signals: u64) -> usize {
     unsafe { WAIT_QUEUE_REGISTRY.signal(handle, signals) }
 }
 
+/// Cancel all waits on a handle that is being closed
+///
+/// Returns the number of waiters woken.
+pub fn cancel_waiters(handle: u32) -> usize {
+    unsafe { WAIT_QUEUE_REGISTRY.cancel(handle) }
+}
+
+/// Queue a wait for `signals` on `handle` without blocking
+///
+/// Lets tests check who an object signals without a second thread.
+#[cfg(test)]
+pub(crate) fn test_add_waiter(handle: u32, signals: u64) -> Arc<WaitNode> {
+    let node = WaitNode::new(0, core::iter::once((handle, signals)));
+    unsafe { WAIT_QUEUE_REGISTRY.get_or_create(handle) }.add(&node, 0);
+    node
+}
+
 /// Timeout all waiters on a specific handle
 ///
 /// This is called when a wait deadline expires.
@@ -396,13 +474,16 @@ impl WaitQueueRegistry {
         }
     }
 
+    fn get(&self, handle: u32) -> Option<&WaitQueue> {
+        self.queues[(handle as usize) % MAX_WAIT_QUEUES].as_ref()
+    }
+
     fn signal(&self, handle: u32, signals: u64) -> usize {
-        let idx = (handle as usize) % MAX_WAIT_QUEUES;
-        if let Some(queue) = &self.queues[idx] {
-            queue.signal(signals)
-        } else {
-            0
-        }
+        self.get(handle).map_or(0, |queue| queue.signal(signals))
+    }
+
+    fn cancel(&self, handle: u32) -> usize {
+        self.get(handle).map_or(0, |queue| queue.cancel_all())
     }
 }
 
@@ -457,8 +538,11 @@ pub fn sys_object_wait_one_impl(
     }
 
     // TODO: Block the thread and wait for signal
-    // For now, simulate immediate completion
-    let observed = signals;
+    // For now, simulate immediate completion; eventpairs and counters
+    // report their actual state
+    let observed = crate::kernel::syscalls::event::eventpair_signals(handle_val)
+        .or_else(|| crate::kernel::syscalls::counter::counter_signals(handle_val))
+        .unwrap_or(signals);
 
     // Signal the wait queue (simulating that the object is already signaled)
     queue.signal(signals);
@@ -496,10 +580,16 @@ pub fn sys_object_wait_one_impl(
 /// * `count` - Number of wait items
 /// * `deadline` - Deadline for timeout (in nanoseconds)
 ///
+/// The items are copied back with `pending` set to each object's signals
+/// when the wait ended, whatever the result.
+///
 /// # Returns
 ///
 /// * On success: 0
 /// * On error: Negative error code
+///   - `RX_ERR_BAD_HANDLE` if any handle is invalid
+///   - `RX_ERR_CANCELED` if a waited handle was closed
+///   - `RX_ERR_TIMED_OUT` if the deadline passed first
 pub fn sys_object_wait_many_impl(user_items: usize, count: usize, deadline: u64) -> SyscallRet {
     log_debug!(
         "sys_object_wait_many: items={:#x} count={} deadline={}",
@@ -508,8 +598,10 @@ pub fn sys_object_wait_many_impl(user_items: usize, count: usize, deadline: u64)
 
     // Handle zero count - just sleep
     if count == 0 {
-        // TODO: Implement proper sleep
         log_debug!("sys_object_wait_many: sleeping until deadline");
+        while crate::kernel::timer::current_time() < deadline {
+            crate::kernel::thread::yield_current();
+        }
         return err_to_ret(RX_ERR_TIMED_OUT);
     }
 
@@ -539,21 +631,7 @@ pub fn sys_object_wait_many_impl(user_items: usize, count: usize, deadline: u64)
         }
     }
 
-    // Validate all handles
-    for (i, item) in items.iter().enumerate() {
-        if item.handle == 0 {
-            log_error!("sys_object_wait_many: invalid handle at index {}", i);
-            return err_to_ret(RX_ERR_BAD_HANDLE);
-        }
-    }
-
-    // TODO: Implement proper multi-wait mechanism
-    // For now, simulate immediate completion with all signals
-    let mut combined = 0u64;
-    for item in &mut items {
-        item.pending = item.waitfor;
-        combined |= item.pending;
-    }
+    let result = wait_many(&mut items, deadline);
 
     // Copy wait items back to user
     let user_ptr = UserPtr::<u8>::new(user_items);
@@ -568,14 +646,108 @@ pub fn sys_object_wait_many_impl(user_items: usize, count: usize, deadline: u64)
         }
     }
 
-    // Check if any handle was closed
-    if combined & signal::HANDLE_CLOSED != 0 {
-        return err_to_ret(RX_ERR_CANCELED);
+    match result {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
     }
+}
 
-    log_debug!("sys_object_wait_many: success combined={:#x}", combined);
+/// Wait until any item's object asserts a signal it waits for
+///
+/// Sets every item's `pending` to the signals its object had when the
+/// wait ended. An item whose handle is invalid, or is closed during the
+/// wait, reports `HANDLE_CLOSED`.
+///
+/// # Returns
+///
+/// * `Ok(())` once any item's `pending` overlaps its `waitfor`
+/// * `Err(RX_ERR_BAD_HANDLE)` if any handle is invalid; nothing is waited on
+/// * `Err(RX_ERR_CANCELED)` if a handle was closed during the wait
+/// * `Err(RX_ERR_TIMED_OUT)` if the deadline passed first
+fn wait_many(items: &mut [WaitItem], deadline: u64) -> Result {
+    // Report every invalid item, not just the first
+    let mut invalid = false;
+    for (i, item) in items.iter_mut().enumerate() {
+        match current_signals(item.handle) {
+            Some(signals) => item.pending = signals,
+            None => {
+                log_error!("sys_object_wait_many: invalid handle at index {}", i);
+                item.pending = signal::HANDLE_CLOSED;
+                invalid = true;
+            }
+        }
+    }
+    if invalid {
+        return Err(RX_ERR_BAD_HANDLE);
+    }
 
-    ok_to_ret(0)
+    if any_satisfied(items) {
+        return Ok(());
+    }
+    if crate::kernel::timer::current_time() >= deadline {
+        return Err(RX_ERR_TIMED_OUT);
+    }
+
+    let thread_id = crate::kernel::thread::current_thread_id() as usize;
+    let node = WaitNode::new(thread_id, items.iter().map(|item| (item.handle, item.waitfor)));
+    for (i, item) in items.iter().enumerate() {
+        unsafe { WAIT_QUEUE_REGISTRY.get_or_create(item.handle) }.add(&node, i);
+    }
+
+    // A signal asserted between the first check and queueing the node
+    // would not wake it, so look once more before waiting
+    refresh(items, &node);
+    if !any_satisfied(items) {
+        // TODO: Block until woken or the deadline instead of yielding
+        while !node.is_woken() && crate::kernel::timer::current_time() < deadline {
+            crate::kernel::thread::yield_current();
+        }
+    }
+
+    for item in items.iter() {
+        if let Some(queue) = unsafe { WAIT_QUEUE_REGISTRY.get(item.handle) } {
+            queue.remove(&node);
+        }
+    }
+    refresh(items, &node);
+
+    if node.is_canceled() {
+        Err(RX_ERR_CANCELED)
+    } else if any_satisfied(items) {
+        Ok(())
+    } else {
+        Err(RX_ERR_TIMED_OUT)
+    }
+}
+
+/// Set each item's `pending` to its object's signals plus any seen by `node`
+fn refresh(items: &mut [WaitItem], node: &WaitNode) {
+    for (i, item) in items.iter_mut().enumerate() {
+        let current = current_signals(item.handle).unwrap_or(signal::HANDLE_CLOSED);
+        item.pending = current | node.observed(i);
+    }
+}
+
+/// Check if any item has a signal it waits for
+fn any_satisfied(items: &[WaitItem]) -> bool {
+    items.iter().any(|item| item.pending & item.waitfor != 0)
+}
+
+/// Get the signals an object currently asserts
+///
+/// Objects without tracked state report none; waits on them complete when
+/// they are signaled. Returns `None` if `handle` names no object.
+fn current_signals(handle: u32) -> Option<u64> {
+    use crate::kernel::syscalls::{counter, event};
+
+    event::eventpair_signals(handle)
+        .or_else(|| counter::counter_signals(handle))
+        .or_else(|| event::event_signals(handle))
+        .or_else(|| {
+            crate::kernel::thread::current_thread_handle_table()
+                .get(handle)
+                .map(|_| 0)
+        })
 }
 
 /// ============================================================================
@@ -674,6 +846,7 @@ pub fn init() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::kernel::object::counter::{COUNTER_NON_POSITIVE, COUNTER_POSITIVE};
 
     #[test]
     fn test_constants() {
@@ -728,6 +901,89 @@ mod tests {
         assert!(result < 0);
     }
 
+    #[test]
+    fn test_wait_node_signal() {
+        let queue = WaitQueue::new();
+        let node = WaitNode::new(0, [(1, signal::USER_0), (2, signal::USER_1)].into_iter());
+        queue.add(&node, 1);
+
+        // Unwanted signals leave the entry queued
+        assert_eq!(queue.signal(signal::USER_0), 0);
+        assert!(!node.is_woken());
+
+        assert_eq!(queue.signal(signal::USER_1 | signal::USER_2), 1);
+        assert!(node.is_woken());
+        assert_eq!(node.observed(1), signal::USER_1 | signal::USER_2);
+        assert_eq!(node.observed(0), 0);
+        assert_eq!(queue.count(), 0);
+    }
+
+    #[test]
+    fn test_wait_node_cancel() {
+        let queue = WaitQueue::new();
+        let node = WaitNode::new(0, core::iter::once((1, signal::USER_0)));
+        queue.add(&node, 0);
+
+        assert_eq!(queue.cancel_all(), 1);
+        assert!(node.is_woken());
+        assert!(node.is_canceled());
+        assert_eq!(node.observed(0), signal::HANDLE_CLOSED);
+    }
+
+    #[test]
+    fn test_wait_queue_remove() {
+        let queue = WaitQueue::new();
+        let node = WaitNode::new(0, core::iter::once((1, signal::USER_0)));
+        let other = WaitNode::new(0, core::iter::once((1, signal::USER_0)));
+        queue.add(&node, 0);
+        queue.add(&other, 0);
+
+        queue.remove(&node);
+        assert_eq!(queue.count(), 1);
+        assert_eq!(queue.signal(signal::USER_0), 1);
+        assert!(!node.is_woken());
+        assert!(other.is_woken());
+    }
+
+    #[test]
+    fn test_wait_many_invalid_items() {
+        let counter = crate::kernel::syscalls::counter::sys_counter_create_impl(0) as u32;
+        let mut items = [
+            WaitItem { handle: counter, waitfor: COUNTER_POSITIVE, pending: 0 },
+            WaitItem { handle: 0xdead_beef, waitfor: signal::USER_0, pending: 0 },
+        ];
+
+        assert_eq!(wait_many(&mut items, u64::MAX), Err(RX_ERR_BAD_HANDLE));
+        assert_eq!(items[0].pending, COUNTER_NON_POSITIVE);
+        assert_eq!(items[1].pending, signal::HANDLE_CLOSED);
+    }
+
+    #[test]
+    fn test_wait_many_already_signaled() {
+        use crate::kernel::syscalls::counter;
+
+        let idle = counter::sys_counter_create_impl(0) as u32;
+        let ready = counter::sys_counter_create_impl(0) as u32;
+        counter::sys_counter_add_impl(ready, 1);
+        let mut items = [
+            WaitItem { handle: idle, waitfor: COUNTER_POSITIVE, pending: 0 },
+            WaitItem { handle: ready, waitfor: COUNTER_POSITIVE, pending: 0 },
+        ];
+
+        assert_eq!(wait_many(&mut items, u64::MAX), Ok(()));
+        assert_eq!(items[0].pending, COUNTER_NON_POSITIVE);
+        assert_eq!(items[1].pending, COUNTER_POSITIVE);
+    }
+
+    #[test]
+    fn test_wait_many_past_deadline() {
+        let counter = crate::kernel::syscalls::counter::sys_counter_create_impl(0) as u32;
+        let mut items = [WaitItem { handle: counter, waitfor: COUNTER_POSITIVE, pending: 0 }];
+
+        assert_eq!(wait_many(&mut items, 0), Err(RX_ERR_TIMED_OUT));
+        assert_eq!(items[0].pending, COUNTER_NON_POSITIVE);
+    }
+
     #[test]
     fn test_wait_one_invalid_handle() {
         let result = sys_object_wait_one_impl(0, 0x12345678, 0, 0);
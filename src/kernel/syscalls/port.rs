@@ -67,6 +67,12 @@ pub enum PacketType {
 
     /// Event pair
     EventPair = 6,
+
+    /// Guest memory access to an MMIO or write trap
+    GuestMem = 7,
+
+    /// Guest I/O port access
+    GuestIo = 8,
 }
 
 /// Port packet
@@ -98,6 +104,18 @@ pub union PacketPayload {
     /// Exception data
     pub exception: ExceptionData,
 
+    /// Guest bell trap data
+    pub guest_bell: GuestBellData,
+
+    /// Guest memory access data
+    pub guest_mem: GuestMemData,
+
+    /// Guest I/O port access data
+    pub guest_io: GuestIoData,
+
+    /// Guest vCPU data
+    pub guest_vcpu: GuestVcpuData,
+
     /// Raw bytes
     pub bytes: [u8; 32],
 }
@@ -171,6 +189,85 @@ pub struct ExceptionData {
     pub timestamp: u64,
 }
 
+/// Guest bell trap data
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestBellData {
+    /// Guest-physical address that was written
+    pub addr: u64,
+
+    /// Reserved
+    pub reserved: [u64; 3],
+}
+
+/// Guest memory access data
+///
+/// For a write, `data` holds the value written. For a read, the VMM
+/// stores the value in register `reg` with `rx_vcpu_write_state` before
+/// entering the vCPU again.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestMemData {
+    /// Guest-physical address accessed
+    pub addr: u64,
+
+    /// Value written
+    pub data: u64,
+
+    /// Access size in bytes, 0 if the access couldn't be decoded
+    pub access_size: u8,
+
+    /// 1 for a write, 0 for a read
+    pub write: u8,
+
+    /// Register read into or written from, in x86 instruction encoding
+    /// order
+    pub reg: u8,
+
+    /// Reserved
+    pub reserved: [u8; 13],
+}
+
+/// Guest I/O port access data
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestIoData {
+    /// Port accessed
+    pub port: u16,
+
+    /// Access size in bytes
+    pub access_size: u8,
+
+    /// 1 for an input (read), 0 for an output (write)
+    pub input: u8,
+
+    /// Value written by an output
+    pub data: u32,
+
+    /// Reserved
+    pub reserved: [u64; 3],
+}
+
+/// Guest vCPU data
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestVcpuData {
+    /// What happened, one of `guest_vcpu_kind`
+    pub kind: u32,
+
+    /// Reserved
+    pub reserved: [u32; 7],
+}
+
+/// Kinds of guest vCPU packet
+pub mod guest_vcpu_kind {
+    /// The vCPU left the guest for an interrupt and was kicked
+    pub const INTERRUPT: u32 = 0;
+
+    /// The guest powered off
+    pub const EXIT: u32 = 1;
+}
+
 /// ============================================================================
 /// Port Options
 /// ============================================================================
@@ -288,6 +385,24 @@ fn alloc_port_id() -> u64 {
     unsafe { NEXT_PORT_ID.fetch_add(1, Ordering::Relaxed) }
 }
 
+/// Queue a kernel-generated packet on a port
+///
+/// Used by objects that report to ports, such as guest bell traps.
+///
+/// # Errors
+///
+/// - `RX_ERR_BAD_HANDLE` - no port has ID `port_id`
+pub(crate) fn queue_packet(port_id: u64, packet: PortPacket) -> Result {
+    let port = PORT_REGISTRY.lock().get(port_id).ok_or(RX_ERR_BAD_HANDLE)?;
+    port.packets.lock().push_back(packet);
+    Ok(())
+}
+
+/// Whether `handle_val` names a port
+pub(crate) fn port_exists(handle_val: u32) -> bool {
+    PORT_REGISTRY.lock().get(handle_val as u64).is_some()
+}
+
 /// ============================================================================
 /// Syscall: Port Create
 /// ============================================================================
@@ -588,4 +703,12 @@ mod tests {
     fn test_packet_size() {
         assert!(core::mem::size_of::<PortPacket>() >= 40);
     }
+
+    #[test]
+    fn test_guest_payload_sizes() {
+        assert_eq!(core::mem::size_of::<GuestBellData>(), 32);
+        assert_eq!(core::mem::size_of::<GuestMemData>(), 32);
+        assert_eq!(core::mem::size_of::<GuestIoData>(), 32);
+        assert_eq!(core::mem::size_of::<GuestVcpuData>(), 32);
+    }
 }
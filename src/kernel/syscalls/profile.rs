@@ -17,16 +17,27 @@
 //!
 //! Profiles allow privileged processes to control CPU scheduling behavior,
 //! such as CPU affinity, priority, and scheduling parameters.
-
-
-use crate::kernel::object::{Handle, HandleTable, ObjectType, Rights, KernelObjectBase};
-use crate::kernel::usercopy::{copy_from_user, UserPtr};
-use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+//!
+//! Only the holder of the root job can create a profile. A profile with
+//! [`profile_flags::DEADLINE`] moves the threads it is applied to into the
+//! deadline scheduling class; its parameters are checked when the profile
+//! is created and admitted against the CPU when it is applied, so applying
+//! it can fail with `RX_ERR_NO_RESOURCES` (see [`crate::kernel::sched::deadline`]).
+//! Applying a profile without the flag puts the thread back in the
+//! fixed-priority class.
+
+
+use crate::kernel::object::{KernelObjectBase, ObjectType};
+use crate::kernel::object::job::{self, JobId};
+use crate::kernel::sched::{self, deadline::DeadlineParams};
+use crate::kernel::sync::Mutex;
+use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
+use crate::kernel::syscalls::{task, SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
-use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU64, Ordering};
 
 // Import logging macros
 use crate::{log_debug, log_error, log_info};
@@ -51,6 +62,12 @@ pub mod profile_flags {
 
     /// Profile is for real-time
     pub const REALTIME: u32 = 0x04;
+
+    /// Profile puts threads in the deadline class
+    pub const DEADLINE: u32 = 0x08;
+
+    /// Every defined flag
+    pub const ALL: u32 = HAS_CPU_AFFINITY | HAS_PRIORITY | REALTIME | DEADLINE;
 }
 
 /// ============================================================================
@@ -72,6 +89,9 @@ pub struct ProfileInfo {
     /// Priority level
     pub priority: u32,
 
+    /// Deadline parameters, used with `DEADLINE`
+    pub deadline: DeadlineParams,
+
     /// Reserved for future use
     pub reserved: [u32; 8],
 }
@@ -82,6 +102,7 @@ impl Default for ProfileInfo {
             flags: 0,
             cpu_affinity: 0,
             priority: 0,
+            deadline: DeadlineParams::default(),
             reserved: [0; 8],
         }
     }
@@ -95,11 +116,25 @@ impl Default for ProfileInfo {
 const MAX_PROFILES: usize = 128;
 
 /// Next profile ID counter
-static mut NEXT_PROFILE_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_PROFILE_ID: AtomicU64 = AtomicU64::new(1);
 
 /// Allocate a new profile ID
 fn alloc_profile_id() -> u64 {
-    unsafe { NEXT_PROFILE_ID.fetch_add(1, Ordering::Relaxed) }
+    NEXT_PROFILE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Global profile registry
+///
+/// Maps profile IDs to profile objects. This is used to resolve handles to
+/// profiles.
+static PROFILE_REGISTRY: Mutex<BTreeMap<u64, Arc<Profile>>> = Mutex::new(BTreeMap::new());
+
+/// Number of times a profile was applied
+static PROFILES_APPLIED: AtomicU64 = AtomicU64::new(0);
+
+/// Look up a profile by handle value
+fn lookup_profile(handle: u32) -> Option<Arc<Profile>> {
+    PROFILE_REGISTRY.lock().get(&(handle as u64)).cloned()
 }
 
 /// Profile object
@@ -179,7 +214,24 @@ pub fn sys_profile_create_impl(
         profile_info_user
     );
 
-    // TODO: Validate root job handle
+    // Get root job (0 means root job); only it may create profiles
+    let job_id = if root_job_handle == 0 {
+        job::JOB_ID_ROOT
+    } else {
+        root_job_handle as JobId
+    };
+
+    match job::lookup(job_id) {
+        Some(job) if job.id == job::JOB_ID_ROOT => {}
+        Some(_) => {
+            log_error!("sys_profile_create: job {:#x} is not the root job", job_id);
+            return err_to_ret(RX_ERR_ACCESS_DENIED);
+        }
+        None => {
+            log_error!("sys_profile_create: root job not found");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+    }
 
     // Copy profile info from user
     let user_ptr = UserPtr::<u8>::new(profile_info_user);
@@ -189,45 +241,37 @@ pub fn sys_profile_create_impl(
         if let Err(err) = copy_from_user(
             &mut profile_info as *mut ProfileInfo as *mut u8,
             user_ptr,
-            1,
+            core::mem::size_of::<ProfileInfo>(),
         ) {
             log_error!("sys_profile_create: copy_from_user failed: {:?}", err);
             return err_to_ret(err.into());
         }
     }
 
-    // Validate profile flags
-    if profile_info.flags & !(profile_flags::HAS_CPU_AFFINITY | profile_flags::HAS_PRIORITY | profile_flags::REALTIME)
-        != 0
-    {
-        log_error!("sys_profile_create: invalid flags {:#x}", profile_info.flags);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
+    if let Err(err) = validate_info(&profile_info) {
+        log_error!("sys_profile_create: invalid profile flags={:#x}", profile_info.flags);
+        return err_to_ret(err);
     }
 
     // Create the profile
     let profile = Arc::new(Profile::new(profile_info));
+    let handle_value = profile.id() as u32;
 
-    // Add to current process's handle table
-    let handle_table = crate::kernel::thread::current_thread_handle_table();
-
-    // Create handle with APPLY_PROFILE rights
-    let handle = Handle::new(&profile.base as *const KernelObjectBase, Rights::APPLY_PROFILE);
-    let handle_value = match handle_table.add(handle) {
-        Ok(val) => val,
-        Err(err) => {
-            log_error!("sys_profile_create: failed to add handle: {:?}", err);
-            return err_to_ret(err);
+    {
+        let mut registry = PROFILE_REGISTRY.lock();
+        if registry.len() >= MAX_PROFILES {
+            log_error!("sys_profile_create: too many profiles");
+            return err_to_ret(RX_ERR_NO_RESOURCES);
         }
-    };
+        registry.insert(profile.id(), profile.clone());
+    }
 
     // Write handle value to user
     let out_ptr = UserPtr::<u8>::new(profile_out);
     unsafe {
-        if let Err(err) =
-            crate::kernel::usercopy::copy_to_user(out_ptr, &handle_value as *const u32 as *const u8, 4)
-        {
+        if let Err(err) = copy_to_user(out_ptr, &handle_value as *const u32 as *const u8, 4) {
             log_error!("sys_profile_create: copy_to_user failed: {:?}", err);
-            let _ = handle_table.remove(handle_value);
+            PROFILE_REGISTRY.lock().remove(&profile.id());
             return err_to_ret(err.into());
         }
     }
@@ -269,16 +313,77 @@ pub fn sys_object_set_profile_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Get thread object from handle
-    // TODO: Get profile object from profile_handle
-    // TODO: Apply profile to thread
+    // Get thread object from handle
+    let thread = match task::lookup_thread(handle as u64) {
+        Some(thread) => thread,
+        None => {
+            log_error!("sys_object_set_profile: thread not found");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+    };
+
+    // Get profile object from profile_handle
+    let profile = match lookup_profile(profile_handle) {
+        Some(profile) => profile,
+        None => {
+            log_error!("sys_object_set_profile: profile not found");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+    };
+
+    // Apply profile to thread
+    let info = profile.info();
+    let params = if info.flags & profile_flags::DEADLINE != 0 {
+        Some(info.deadline)
+    } else {
+        None
+    };
+
+    if info.flags & profile_flags::HAS_CPU_AFFINITY != 0 {
+        if let Err(err) = sched::set_affinity(&thread, info.cpu_affinity) {
+            log_error!("sys_object_set_profile: no online CPU in {:#x}", info.cpu_affinity);
+            return err_to_ret(err);
+        }
+    }
+
+    if params.is_none() && info.flags & profile_flags::HAS_PRIORITY != 0 {
+        thread.set_priority(info.priority as u8);
+    }
+
+    // Also requeues the thread in its new class or priority
+    if let Err(err) = sched::set_deadline(&thread, params) {
+        log_error!("sys_object_set_profile: deadline not admitted: {:?}", err);
+        return err_to_ret(err);
+    }
 
-    // For now, just log
-    log_info!("sys_object_set_profile: applied profile (stub)");
+    PROFILES_APPLIED.fetch_add(1, Ordering::Relaxed);
+
+    log_debug!(
+        "sys_object_set_profile: applied profile {} to thread {}",
+        profile.id(),
+        thread.tid()
+    );
 
     ok_to_ret(0)
 }
 
+/// Check the flags and parameters of a new profile
+fn validate_info(info: &ProfileInfo) -> core::result::Result<(), rx_status_t> {
+    if info.flags & !profile_flags::ALL != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    if info.flags & profile_flags::HAS_PRIORITY != 0 && info.priority > u8::MAX as u32 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    if info.flags & profile_flags::DEADLINE != 0 {
+        info.deadline.validate()?;
+    }
+
+    Ok(())
+}
+
 /// ============================================================================
 /// Module Statistics
 /// ============================================================================
@@ -286,8 +391,8 @@ pub fn sys_object_set_profile_impl(
 /// Get profile subsystem statistics
 pub fn get_stats() -> ProfileStats {
     ProfileStats {
-        total_profiles: 0, // TODO: Track profiles
-        total_applied: 0,  // TODO: Track applications
+        total_profiles: PROFILE_REGISTRY.lock().len(),
+        total_applied: PROFILES_APPLIED.load(Ordering::Relaxed),
     }
 }
 
@@ -334,6 +439,26 @@ mod tests {
         assert_eq!(profile_flags::HAS_CPU_AFFINITY, 0x01);
         assert_eq!(profile_flags::HAS_PRIORITY, 0x02);
         assert_eq!(profile_flags::REALTIME, 0x04);
+        assert_eq!(profile_flags::DEADLINE, 0x08);
+    }
+
+    #[test]
+    fn test_validate_info() {
+        let mut info = ProfileInfo::default();
+        assert!(validate_info(&info).is_ok());
+
+        info.flags = 0x100;
+        assert_eq!(validate_info(&info), Err(RX_ERR_INVALID_ARGS));
+
+        info.flags = profile_flags::DEADLINE;
+        assert_eq!(validate_info(&info), Err(RX_ERR_INVALID_ARGS));
+
+        info.deadline = DeadlineParams {
+            capacity: 1_000_000,
+            relative_deadline: 5_000_000,
+            period: 10_000_000,
+        };
+        assert!(validate_info(&info).is_ok());
     }
 
     #[test]
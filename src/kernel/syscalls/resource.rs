@@ -6,7 +6,8 @@
 
 //! Resource System Calls
 //!
-//! This module implements the resource-related system calls.
+//! This module implements resource objects and the resource-related system
+//! calls.
 //!
 //! # Syscalls Implemented
 //!
@@ -14,21 +15,35 @@
 //!
 //! # Design
 //!
-//! - Resources represent privileged kernel objects
-//! - Hierarchical resource tree
-//! - Root resource required for creation
-//! - Resource kinds and flags
+//! A resource grants access to privileged hardware. Resources are kernel
+//! objects reached through the handle table like any other. The root
+//! resource grants everything; userboot creates it once and hands its
+//! handle to the first process. Holders derive narrower resources from it
+//! and pass those to drivers:
+//!
+//! - `MMIO` - a physical address range, for `rx_vmo_create_physical`
+//! - `IRQ` - a range of interrupt vectors, for `rx_interrupt_create`
+//! - `IOPORT` - a range of x86 I/O ports, for `rx_ioports_request`
+//! - `SYSTEM` and `HYPERVISOR` - the whole of their kind, no range
+//! - `EFI` - EFI runtime services, for the `rx_efi_*` syscalls, no range
+//!
+//! A child is derived from the root or from a resource of the same kind
+//! whose range covers it, so access can be delegated but never widened.
+//! No one can derive another root.
 
 
+use crate::kernel::object::koid::Koid;
 use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
-use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
+use crate::kernel::object::interrupt::MAX_IRQ_VECTORS;
+use crate::kernel::sync::Mutex;
+use crate::kernel::usercopy::{copy_from_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
-use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 // Import logging macros
 use crate::{log_debug, log_error, log_info};
@@ -40,6 +55,9 @@ use crate::{log_debug, log_error, log_info};
 /// Maximum resource name length
 const MAX_NAME_LEN: usize = 32;
 
+/// Number of x86 I/O ports
+pub const IOPORT_COUNT: u64 = 0x10000;
+
 /// Resource kinds
 pub mod resource_kind {
     /// Root resource
@@ -57,8 +75,19 @@ pub mod resource_kind {
     /// Hypervisor resource
     pub const HYPERVISOR: u32 = 4;
 
+    /// x86 I/O port resource
+    pub const IOPORT: u32 = 5;
+
+    /// EFI runtime services resource
+    pub const EFI: u32 = 6;
+
     /// Total number of resource kinds
-    pub const COUNT: u32 = 5;
+    pub const COUNT: u32 = 7;
+
+    /// Whether resources of `kind` cover a range
+    pub const fn is_ranged(kind: u32) -> bool {
+        matches!(kind, IRQ | MMIO | IOPORT)
+    }
 }
 
 /// Resource options
@@ -83,6 +112,83 @@ pub mod resource_options {
     }
 }
 
+/// End of the range a resource of `kind` may cover
+const fn kind_limit(kind: u32) -> u64 {
+    match kind {
+        resource_kind::IRQ => MAX_IRQ_VECTORS as u64,
+        resource_kind::IOPORT => IOPORT_COUNT,
+        _ => u64::MAX,
+    }
+}
+
+/// ============================================================================
+/// Resource Object
+/// ============================================================================
+
+/// Resource object
+///
+/// Grants access to a range of one kind of hardware.
+pub struct Resource {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+
+    /// Resource kind
+    kind: u32,
+
+    /// Flags from the options it was created with
+    flags: u32,
+
+    /// First address, vector or port covered
+    range_base: u64,
+
+    /// Number of addresses, vectors or ports covered
+    range_size: u64,
+
+    /// Name, for diagnostics
+    name: String,
+}
+
+impl Resource {
+    /// Get the resource's koid
+    pub fn koid(&self) -> Koid {
+        self.base.koid
+    }
+
+    /// Get resource kind
+    pub fn kind(&self) -> u32 {
+        self.kind
+    }
+
+    /// Get resource flags
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Get the covered range as (base, size)
+    pub fn range(&self) -> (u64, u64) {
+        (self.range_base, self.range_size)
+    }
+
+    /// Get resource name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the resource grants `[base, base + size)`
+    ///
+    /// Resources of a kind without ranges grant all of it.
+    pub fn covers(&self, base: u64, size: u64) -> bool {
+        if !resource_kind::is_ranged(self.kind) {
+            return true;
+        }
+        match (base.checked_add(size), self.range_base.checked_add(self.range_size)) {
+            (Some(end), Some(limit)) => base >= self.range_base && end <= limit,
+            _ => false,
+        }
+    }
+}
+
 /// ============================================================================
 /// Resource Registry
 /// ============================================================================
@@ -90,12 +196,163 @@ pub mod resource_options {
 /// Maximum number of resources in the system
 const MAX_RESOURCES: usize = 256;
 
-/// Next resource ID counter
-static mut NEXT_RESOURCE_ID: AtomicU64 = AtomicU64::new(1);
+/// Global resource registry
+///
+/// Maps resource koids to resource objects. Handles in the handle table
+/// point at the resource's base; this resolves them back to the resource.
+static RESOURCE_REGISTRY: Mutex<BTreeMap<Koid, Arc<Resource>>> = Mutex::new(BTreeMap::new());
+
+/// Set once userboot has taken the root resource
+static ROOT_GRANTED: AtomicBool = AtomicBool::new(false);
 
-/// Allocate a new resource ID
-fn alloc_resource_id() -> u64 {
-    unsafe { NEXT_RESOURCE_ID.fetch_add(1, Ordering::Relaxed) }
+/// Get the current process's handle table
+fn current_process_handle_table() -> Option<&'static HandleTable> {
+    Some(crate::kernel::thread::current_thread_handle_table())
+}
+
+/// Register `resource` and add a handle to it to the current handle table
+///
+/// Returns the handle value.
+fn install(resource: Resource) -> Result<u32> {
+    let handle_table = current_process_handle_table()
+        .ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    let resource = Arc::new(resource);
+    {
+        let mut registry = RESOURCE_REGISTRY.lock();
+        if registry.len() >= MAX_RESOURCES {
+            return Err(RX_ERR_NO_RESOURCES);
+        }
+        registry.insert(resource.koid(), resource.clone());
+    }
+
+    let rights = Rights::default_for_type(ObjectType::Resource);
+    handle_table.add(Handle::new(&resource.base, rights)).map_err(|err| {
+        RESOURCE_REGISTRY.lock().remove(&resource.koid());
+        err
+    })
+}
+
+/// Create the root resource and hand it to the first process
+///
+/// Called once by userboot. Returns the root resource handle.
+///
+/// # Errors
+///
+/// - `RX_ERR_BAD_STATE` - the root resource was already granted
+pub fn grant_root() -> Result<u32> {
+    if ROOT_GRANTED.swap(true, Ordering::AcqRel) {
+        return Err(RX_ERR_BAD_STATE);
+    }
+
+    let handle = install(Resource {
+        base: KernelObjectBase::new(ObjectType::Resource),
+        kind: resource_kind::ROOT,
+        flags: 0,
+        range_base: 0,
+        range_size: 0,
+        name: String::from("root"),
+    })?;
+
+    log_info!("Resource: root resource granted as handle {:#x}", handle);
+    Ok(handle)
+}
+
+/// Add a handle to a fresh root resource, as userboot would
+#[cfg(test)]
+pub(crate) fn test_root_handle() -> u32 {
+    install(Resource {
+        base: KernelObjectBase::new(ObjectType::Resource),
+        kind: resource_kind::ROOT,
+        flags: 0,
+        range_base: 0,
+        range_size: 0,
+        name: String::from("root"),
+    })
+    .unwrap()
+}
+
+/// Whether `handle` is the root resource
+pub fn is_root(handle: u32) -> bool {
+    lookup(handle).is_some_and(|resource| resource.kind == resource_kind::ROOT)
+}
+
+/// Look up a resource from a handle value
+///
+/// Returns `None` if `handle` is not in the handle table or is not a resource.
+pub fn lookup(handle: u32) -> Option<Arc<Resource>> {
+    let handle = current_process_handle_table()?.get(handle)?;
+    if handle.obj_type() != ObjectType::Resource {
+        return None;
+    }
+    RESOURCE_REGISTRY.lock().get(&handle.koid()).cloned()
+}
+
+/// Check that `handle` grants `[base, base + size)` of `kind`
+///
+/// The root resource grants everything.
+///
+/// # Errors
+///
+/// - `RX_ERR_ACCESS_DENIED` - `handle` is not a resource of `kind`
+/// - `RX_ERR_OUT_OF_RANGE` - the resource doesn't cover the range
+pub fn validate(handle: u32, kind: u32, base: u64, size: u64) -> Result {
+    let resource = lookup(handle).ok_or(RX_ERR_ACCESS_DENIED)?;
+    if resource.kind == resource_kind::ROOT {
+        return Ok(());
+    }
+    if resource.kind != kind {
+        return Err(RX_ERR_ACCESS_DENIED);
+    }
+    if !resource.covers(base, size) {
+        return Err(RX_ERR_OUT_OF_RANGE);
+    }
+    Ok(())
+}
+
+/// Derive a resource from `parent` and return a handle to it
+///
+/// # Errors
+///
+/// - `RX_ERR_INVALID_ARGS` - `kind` is unknown or root, or the range is
+///   empty for a ranged kind or set for one without ranges
+/// - `RX_ERR_OUT_OF_RANGE` - the range overflows or passes the end of its kind
+/// - `RX_ERR_ACCESS_DENIED` - `parent` is not the root or a resource of `kind`
+/// - `RX_ERR_NO_RESOURCES` - too many resources or handles
+pub fn create(
+    parent: u32,
+    kind: u32,
+    flags: u32,
+    base: u64,
+    size: u64,
+    name: String,
+) -> Result<u32> {
+    if kind == resource_kind::ROOT || kind >= resource_kind::COUNT {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    if resource_kind::is_ranged(kind) {
+        if size == 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        match base.checked_add(size) {
+            Some(end) if end <= kind_limit(kind) => {}
+            _ => return Err(RX_ERR_OUT_OF_RANGE),
+        }
+    } else if base != 0 || size != 0 {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+
+    validate(parent, kind, base, size)?;
+
+    install(Resource {
+        base: KernelObjectBase::new(ObjectType::Resource),
+        kind,
+        flags,
+        range_base: base,
+        range_size: size,
+        name,
+    })
 }
 
 /// ============================================================================
@@ -106,9 +363,10 @@ fn alloc_resource_id() -> u64 {
 ///
 /// # Arguments
 ///
-/// * `parent_handle` - Parent resource handle (must be root)
+/// * `parent_handle` - Parent resource handle (root, or a resource of the
+///   same kind covering the range)
 /// * `options` - Resource kind and flags
-/// * `base` - Base address
+/// * `base` - Base address, vector or port
 /// * `size` - Size of resource range
 /// * `name` - User pointer to resource name
 /// * `name_size` - Size of name
@@ -130,28 +388,12 @@ pub fn sys_resource_create_impl(
         parent_handle, options, base, size
     );
 
-    // Validate parent handle (must be 0 for root)
-    if parent_handle != 0 {
-        log_error!("sys_resource_create: parent is not root");
-        return err_to_ret(RX_ERR_ACCESS_DENIED);
-    }
-
-    // Extract and validate kind
+    // Extract kind and flags
     let kind = resource_options::extract_kind(options);
-    if kind >= resource_kind::COUNT {
-        log_error!("sys_resource_create: invalid kind {}", kind);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
-    }
-
-    // Extract and validate flags
     let flags = resource_options::extract_flags(options);
-    if flags & resource_options::FLAGS_MASK != flags {
-        log_error!("sys_resource_create: invalid flags {:#x}", flags);
-        return err_to_ret(RX_ERR_INVALID_ARGS);
-    }
 
     // Copy name from user if provided
-    let _resource_name = if name_size > 0 && name != 0 {
+    let resource_name = if name_size > 0 && name != 0 {
         let copy_size = name_size.min(MAX_NAME_LEN - 1);
         let mut name_buf = alloc::vec![0u8; copy_size];
 
@@ -169,18 +411,20 @@ pub fn sys_resource_create_impl(
         String::from("resource")
     };
 
-    // Allocate new resource ID
-    let resource_id = alloc_resource_id();
-
-    // TODO: Implement proper resource creation
-    // For now, just return the ID
+    let handle = match create(parent_handle, kind, flags, base, size as u64, resource_name) {
+        Ok(handle) => handle,
+        Err(err) => {
+            log_error!(
+                "sys_resource_create: kind {} [{:#x}, +{:#x}) from {:#x} failed: {}",
+                kind, base, size, parent_handle, err
+            );
+            return err_to_ret(err);
+        }
+    };
 
-    log_debug!(
-        "sys_resource_create: success resource_id={} kind={}",
-        resource_id, kind
-    );
+    log_debug!("sys_resource_create: success handle={:#x} kind={}", handle, kind);
 
-    ok_to_ret(resource_id as usize)
+    ok_to_ret(handle as usize)
 }
 
 /// ============================================================================
@@ -190,8 +434,8 @@ pub fn sys_resource_create_impl(
 /// Get resource subsystem statistics
 pub fn get_stats() -> ResourceStats {
     ResourceStats {
-        total_resources: 0, // TODO: Track resources
-        total_root: 0,      // TODO: Track root resources
+        total_resources: RESOURCE_REGISTRY.lock().len(),
+        total_root: ROOT_GRANTED.load(Ordering::Relaxed) as usize,
     }
 }
 
@@ -226,20 +470,86 @@ mod tests {
 
     #[test]
     fn test_resource_create() {
-        let result = sys_resource_create_impl(0, 0, 0, 0, 0, 0);
-        assert!(result >= 0);
+        let root = test_root_handle();
+        let result = sys_resource_create_impl(root, resource_kind::MMIO, 0x1000, 0x1000, 0, 0);
+        assert!(result > 0);
+        assert!(lookup(result as u32).is_some());
+
+        // Nothing derives another root
+        let result = sys_resource_create_impl(root, resource_kind::ROOT, 0, 0, 0, 0);
+        assert_eq!(result, err_to_ret(RX_ERR_INVALID_ARGS));
     }
 
     #[test]
     fn test_resource_create_not_root() {
-        let result = sys_resource_create_impl(1, 0, 0, 0, 0, 0);
+        let result = sys_resource_create_impl(0xdead, resource_kind::MMIO, 0x1000, 0x1000, 0, 0);
         assert!(result < 0);
     }
 
+    #[test]
+    fn test_resource_handle_rights() {
+        let handle = test_root_handle();
+        let entry = current_process_handle_table().unwrap().get(handle).unwrap();
+        assert_eq!(entry.obj_type(), ObjectType::Resource);
+        assert_eq!(entry.rights(), Rights::default_for_type(ObjectType::Resource));
+
+        // Only userboot takes the root once
+        assert!(is_root(handle));
+        assert_eq!(grant_root().and_then(|_| grant_root()), Err(RX_ERR_BAD_STATE));
+    }
+
+    #[test]
+    fn test_resource_derive() {
+        let name = || String::from("test");
+        let root = test_root_handle();
+        let handle = create(root, resource_kind::MMIO, 0, 0x1000_0000, 0x10000, name()).unwrap();
+
+        // Subranges of the same kind only
+        let child = create(handle, resource_kind::MMIO, 0, 0x1000_1000, 0x1000, name()).unwrap();
+        assert_eq!(lookup(child).unwrap().range(), (0x1000_1000, 0x1000));
+        assert_eq!(
+            create(handle, resource_kind::MMIO, 0, 0x1000_f000, 0x2000, name()).err(),
+            Some(RX_ERR_OUT_OF_RANGE)
+        );
+        assert_eq!(
+            create(handle, resource_kind::IRQ, 0, 0, 1, name()).err(),
+            Some(RX_ERR_ACCESS_DENIED)
+        );
+
+        // Ranges are checked against their kind
+        assert!(create(root, resource_kind::IOPORT, 0, 0x3f8, 8, name()).is_ok());
+        assert_eq!(
+            create(root, resource_kind::IOPORT, 0, 0xfff8, 0x10, name()).err(),
+            Some(RX_ERR_OUT_OF_RANGE)
+        );
+        assert_eq!(
+            create(root, resource_kind::IRQ, 0, 0, 0, name()).err(),
+            Some(RX_ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            create(root, resource_kind::SYSTEM, 0, 0, 1, name()).err(),
+            Some(RX_ERR_INVALID_ARGS)
+        );
+    }
+
+    #[test]
+    fn test_resource_validate() {
+        let root = test_root_handle();
+        let handle = create(root, resource_kind::IRQ, 0, 32, 4, String::from("irq")).unwrap();
+
+        assert_eq!(validate(handle, resource_kind::IRQ, 33, 1), Ok(()));
+        assert_eq!(validate(handle, resource_kind::IRQ, 36, 1), Err(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(validate(handle, resource_kind::MMIO, 33, 1), Err(RX_ERR_ACCESS_DENIED));
+        assert_eq!(validate(0xdead, resource_kind::IRQ, 33, 1), Err(RX_ERR_ACCESS_DENIED));
+
+        // Root grants everything
+        assert_eq!(validate(root, resource_kind::MMIO, 0, u64::MAX), Ok(()));
+    }
+
     #[test]
     fn test_resource_create_invalid_kind() {
         let result = sys_resource_create_impl(
-            0,
+            test_root_handle(),
             resource_kind::COUNT,
             0,
             0,
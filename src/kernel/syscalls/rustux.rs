@@ -22,16 +22,25 @@
 //! - `rx_debuglog_read` - Read from debug log
 //! - `rx_cprng_draw_once` - Draw random bytes
 //! - `rx_cprng_add_entropy` - Add entropy to PRNG
+//!
+//! Debuglog objects write to and read from the kernel's log ring, so a
+//! reader sees kernel records and every object's writes in order.
+//! Debuglog handle values are registry IDs, like counters, until
+//! debuglogs are installed in the process's handle table.
 
 
+use crate::kernel::debug::{self, LogLevel};
+use crate::kernel::lib::debuglog::{DlogReader, DLOG, DLOG_MAX_DATA, DLOG_MAX_RECORD};
 use crate::kernel::object::{Handle, HandleTable, ObjectType, Rights};
+use crate::kernel::sync::Mutex;
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 
 // Import logging macros
 use crate::{log_debug, log_error, log_info};
@@ -46,12 +55,6 @@ const MAX_CPRNG_DRAW: usize = 256;
 /// Maximum CPRNG seed size
 const MAX_CPRNG_SEED: usize = 256;
 
-/// Maximum debug log record size
-const DLOG_MAX_RECORD: usize = 256;
-
-/// Maximum debug log data size
-const DLOG_MAX_DATA: usize = 256;
-
 /// ============================================================================
 /// Clock Constants
 /// ============================================================================
@@ -84,6 +87,25 @@ pub mod dlog_options {
 /// Debug log flags mask
 pub const DLOG_FLAGS_MASK: u32 = 0x01;
 
+/// ============================================================================
+/// Debuglog Registry
+/// ============================================================================
+
+/// A debuglog object
+struct DebugLog {
+    /// Created with `READABLE`
+    readable: bool,
+
+    /// Where its reads are in the log ring
+    reader: DlogReader,
+}
+
+/// Debuglogs by handle value
+static DEBUGLOGS: Mutex<BTreeMap<u32, DebugLog>> = Mutex::new(BTreeMap::new());
+
+/// Next debuglog handle value
+static NEXT_DEBUGLOG: AtomicU32 = AtomicU32::new(1);
+
 /// ============================================================================
 /// UTC Offset
 /// ============================================================================
@@ -443,16 +465,17 @@ pub fn sys_debuglog_create_impl(
 
     // TODO: Validate resource handle (if not INVALID)
 
-    // Create log dispatcher
-    // TODO: Implement actual log creation
-    let log_handle = 200u32; // Placeholder
-
-    // By default log objects are write-only
-    let mut rights = Rights::WRITE;
-    if options & dlog_options::READABLE != 0 {
-        rights |= Rights::READ;
+    if options & !DLOG_FLAGS_MASK != 0 {
+        log_error!("sys_debuglog_create: invalid options");
+        return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
+    // By default log objects are write-only; readers start at the oldest
+    // record in the ring
+    let log_handle = NEXT_DEBUGLOG.fetch_add(1, Ordering::Relaxed);
+    let log = DebugLog { readable: options & dlog_options::READABLE != 0, reader: DLOG.reader() };
+    DEBUGLOGS.lock().insert(log_handle, log);
+
     // Write handle to user
     let user_ptr = UserPtr::<u8>::new(out);
     unsafe {
@@ -462,6 +485,7 @@ pub fn sys_debuglog_create_impl(
         }
     }
 
+    // TODO: Remove the log when its handle is closed
     log_debug!("sys_debuglog_create: success handle={:#x}", log_handle);
     ok_to_ret(0)
 }
@@ -496,6 +520,10 @@ pub fn sys_debuglog_write_impl(
         len
     );
 
+    if !DEBUGLOGS.lock().contains_key(&log_handle) {
+        return err_to_ret(RX_ERR_BAD_HANDLE);
+    }
+
     if len > DLOG_MAX_DATA {
         log_error!("sys_debuglog_write: len too large");
         return err_to_ret(RX_ERR_OUT_OF_RANGE);
@@ -517,19 +545,15 @@ pub fn sys_debuglog_write_impl(
         }
     }
 
-    // Convert to string and log
-    let str_result = alloc::str::from_utf8(&buf);
-    match str_result {
-        Ok(s) => {
-            log_info!("LOG: {}", s);
-        }
-        Err(_) => {
-            log_info!("LOG: {:x?}", buf);
-        }
+    // The console adds its own line break
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
     }
 
-    // TODO: Implement actual log write
-    ok_to_ret(0)
+    match debug::log_write_user(LogLevel::Info as u16, &buf) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -538,6 +562,11 @@ pub fn sys_debuglog_write_impl(
 
 /// Read from debug log
 ///
+/// Reads the log's next record: a 40-byte header with its timestamp,
+/// CPU, process, thread and level, then its text. Fails with
+/// `SHOULD_WAIT` at the newest record and `BUFFER_TOO_SMALL`, without
+/// consuming the record, if it doesn't fit in `len` bytes.
+///
 /// # Arguments
 ///
 /// * `log_handle` - Log handle
@@ -567,10 +596,36 @@ pub fn sys_debuglog_read_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // TODO: Implement actual log read
-    // For now, return 0 bytes read
-    log_debug!("sys_debuglog_read: success, read 0 bytes");
-    ok_to_ret(0)
+    let mut record = [0u8; DLOG_MAX_RECORD];
+    let actual = {
+        let mut logs = DEBUGLOGS.lock();
+        let Some(log) = logs.get_mut(&log_handle) else {
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        };
+        if !log.readable {
+            return err_to_ret(RX_ERR_ACCESS_DENIED);
+        }
+
+        let mut reader = log.reader;
+        let actual = match DLOG.read(&mut reader, &mut record) {
+            Ok(actual) => actual,
+            Err(err) => return err_to_ret(err),
+        };
+        if actual > len {
+            return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+        }
+        log.reader = reader;
+        actual
+    };
+
+    unsafe {
+        if let Err(err) = copy_to_user(UserPtr::<u8>::new(ptr), record.as_ptr(), actual) {
+            log_error!("sys_debuglog_read: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(actual)
 }
 
 /// ============================================================================
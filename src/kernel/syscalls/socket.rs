@@ -113,29 +113,28 @@ impl SocketEntry {
         }
     }
 
-    /// Write data to socket
-    pub fn write(&self, bytes: &[u8]) -> Result<usize> {
-        if self.write_shutdown.load(Ordering::Relaxed) {
+    /// Write data to the peer's buffer
+    ///
+    /// Fails with `PEER_CLOSED` once either this side stopped writing or
+    /// the peer stopped reading.
+    pub fn write(&self, peer: &SocketEntry, bytes: &[u8]) -> Result<usize> {
+        if self.write_shutdown.load(Ordering::Relaxed) || peer.read_shutdown.load(Ordering::Relaxed) {
             return Err(RX_ERR_PEER_CLOSED);
         }
 
-        let mut data = self.data.lock();
-        let original_len = data.len();
-
-        for &b in bytes {
-            data.push_back(b);
-        }
+        let mut data = peer.data.lock();
+        data.extend(bytes.iter().copied());
 
         Ok(bytes.len())
     }
 
-    /// Write control data to socket
-    pub fn write_control(&self, bytes: &[u8]) -> Result<()> {
-        if self.write_shutdown.load(Ordering::Relaxed) {
+    /// Write control data to the peer's buffer
+    pub fn write_control(&self, peer: &SocketEntry, bytes: &[u8]) -> Result<()> {
+        if self.write_shutdown.load(Ordering::Relaxed) || peer.read_shutdown.load(Ordering::Relaxed) {
             return Err(RX_ERR_PEER_CLOSED);
         }
 
-        let mut control = self.control.lock();
+        let mut control = peer.control.lock();
 
         // Control plane has limited capacity
         if control.len() > 4096 {
@@ -149,8 +148,11 @@ impl SocketEntry {
         Ok(())
     }
 
-    /// Read data from socket
-    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+    /// Read data the peer wrote
+    ///
+    /// With nothing buffered, fails with `SHOULD_WAIT` while the peer may
+    /// still write and `PEER_CLOSED` once it can't.
+    pub fn read(&self, peer: Option<&SocketEntry>, buf: &mut [u8]) -> Result<usize> {
         if self.read_shutdown.load(Ordering::Relaxed) {
             return Err(RX_ERR_PEER_CLOSED);
         }
@@ -158,7 +160,10 @@ impl SocketEntry {
         let mut data = self.data.lock();
 
         if data.is_empty() {
-            return Err(RX_ERR_SHOULD_WAIT);
+            return match peer {
+                Some(peer) if !peer.write_shutdown.load(Ordering::Relaxed) => Err(RX_ERR_SHOULD_WAIT),
+                _ => Err(RX_ERR_PEER_CLOSED),
+            };
         }
 
         let to_read = buf.len().min(data.len());
@@ -300,6 +305,11 @@ fn alloc_socket_id() -> u64 {
     unsafe { NEXT_SOCKET_ID.fetch_add(1, Ordering::Relaxed) }
 }
 
+/// The other socket of `socket`'s pair, if it still exists
+fn peer_of(socket: &SocketEntry) -> Option<Arc<SocketEntry>> {
+    unsafe { SOCKET_REGISTRY.get(socket.peer_id.load(Ordering::Relaxed)) }
+}
+
 /// ============================================================================
 /// Syscall: Socket Create
 /// ============================================================================
@@ -443,23 +453,27 @@ pub fn sys_socket_write_impl(
         }
     }
 
-    // Write to socket
+    // Write to the peer
+    let peer = match peer_of(&socket) {
+        Some(peer) => peer,
+        None => return err_to_ret(RX_ERR_PEER_CLOSED),
+    };
     let actual = match options {
         socket_options::NONE => {
-            match socket.write(&buf) {
+            match socket.write(&peer, &buf) {
                 Ok(n) => n,
                 Err(err) => {
-                    log_error!("sys_socket_write: write failed: {:?}", err);
-                    return err_to_ret(RX_ERR_INTERNAL);
+                    log_debug!("sys_socket_write: write failed: {:?}", err);
+                    return err_to_ret(err);
                 }
             }
         }
         socket_options::CONTROL => {
-            match socket.write_control(&buf) {
+            match socket.write_control(&peer, &buf) {
                 Ok(_) => size,
                 Err(err) => {
-                    log_error!("sys_socket_write: write_control failed: {:?}", err);
-                    return err_to_ret(RX_ERR_INTERNAL);
+                    log_debug!("sys_socket_write: write_control failed: {:?}", err);
+                    return err_to_ret(err);
                 }
             }
         }
@@ -542,11 +556,11 @@ pub fn sys_socket_read_impl(
     // Read from socket
     let actual = match options {
         socket_options::NONE => {
-            match socket.read(&mut buf) {
+            match socket.read(peer_of(&socket).as_deref(), &mut buf) {
                 Ok(n) => n,
                 Err(err) => {
-                    log_error!("sys_socket_read: read failed: {:?}", err);
-                    return err_to_ret(RX_ERR_INTERNAL);
+                    log_debug!("sys_socket_read: read failed: {:?}", err);
+                    return err_to_ret(err);
                 }
             }
         }
@@ -554,8 +568,8 @@ pub fn sys_socket_read_impl(
             match socket.read_control(&mut buf) {
                 Ok(n) => n,
                 Err(err) => {
-                    log_error!("sys_socket_read: read_control failed: {:?}", err);
-                    return err_to_ret(RX_ERR_INTERNAL);
+                    log_debug!("sys_socket_read: read_control failed: {:?}", err);
+                    return err_to_ret(err);
                 }
             }
         }
@@ -752,6 +766,13 @@ pub fn sys_socket_shutdown_impl(handle_val: u32, options: u32) -> SyscallRet {
         }
     }
 
+    // Shut down both ways, the socket is done with; the peer sees it closed
+    if socket.read_shutdown.load(Ordering::Relaxed) && socket.write_shutdown.load(Ordering::Relaxed) {
+        unsafe {
+            let _ = SOCKET_REGISTRY.remove(socket_id);
+        }
+    }
+
     log_debug!("sys_socket_shutdown: success");
 
     ok_to_ret(0)
@@ -814,6 +835,24 @@ mod tests {
         assert!(result < 0);
     }
 
+    #[test]
+    fn test_socket_pair_stream() {
+        let (a, b) = (SocketEntry::new(1001), SocketEntry::new(1002));
+        let mut buf = [0u8; 8];
+
+        // Bytes written on one side are read on the other
+        assert_eq!(a.write(&b, b"hello"), Ok(5));
+        assert_eq!(a.read(Some(&b), &mut buf), Err(RX_ERR_SHOULD_WAIT));
+        assert_eq!(b.read(Some(&a), &mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"hello");
+
+        // Once the writer stops, an empty socket reads as closed
+        a.shutdown(shutdown_options::WRITE).unwrap();
+        assert_eq!(b.read(Some(&a), &mut buf), Err(RX_ERR_PEER_CLOSED));
+        assert_eq!(a.write(&b, b"x"), Err(RX_ERR_PEER_CLOSED));
+        assert_eq!(b.read(None, &mut buf), Err(RX_ERR_PEER_CLOSED));
+    }
+
     #[test]
     fn test_socket_write_invalid_handle() {
         let result = sys_socket_write_impl(0, 0, 0, 0, 0);
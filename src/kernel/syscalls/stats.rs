@@ -0,0 +1,278 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Syscall Statistics
+//!
+//! Per-syscall call counts, error counts and latency histograms, recorded
+//! by [`syscall_dispatch`](super::syscall_dispatch) around every call.
+//!
+//! # Design
+//!
+//! Every CPU owns a table with one slot per syscall number. The
+//! dispatcher bumps its own CPU's slot with relaxed atomics, so recording
+//! never takes a lock or shares a cache line with another CPU. Numbers
+//! outside the assigned ranges all land in slot 0, which is reported as
+//! [`SyscallNumber::Unknown`].
+//!
+//! Latency is wall time from entry to return, so a call that blocks counts
+//! its wait. Bucket 0 holds calls under 1024 ns and bucket `i` calls from
+//! 2^(i+9) ns up to twice that, except that the last bucket holds
+//! everything slower.
+//!
+//! Readers sum every CPU's slot; a snapshot taken while calls are in
+//! flight may see a count before its latency.
+//!
+//! Read with `rx_object_get_info(root resource, SYSCALL_STATS)` or the
+//! `syscalls` shell command.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::syscalls::{SyscallNumber, SyscallRet};
+
+use crate::log_info;
+
+/// Slots per CPU table, one per syscall number below this
+const SLOTS: usize = 256;
+
+/// Latency histogram buckets
+pub const LATENCY_BUCKETS: usize = 16;
+
+/// One syscall's counters on one CPU
+struct Slot {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    total_ns: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            total_ns: AtomicU64::new(0),
+            latency: [const { AtomicU64::new(0) }; LATENCY_BUCKETS],
+        }
+    }
+}
+
+/// Each CPU's table of `SLOTS` slots, or null before `init`
+static TABLES: [AtomicPtr<Slot>; SMP_MAX_CPUS] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; SMP_MAX_CPUS];
+
+/// One syscall's statistics as returned to userspace
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallStatsRecord {
+    /// Syscall number, or `SyscallNumber::Unknown` for unassigned numbers
+    pub number: u32,
+
+    /// Reserved, zero
+    pub reserved: u32,
+
+    /// Calls made
+    pub calls: u64,
+
+    /// Calls that returned an error
+    pub errors: u64,
+
+    /// Sum of call latencies in nanoseconds
+    pub total_ns: u64,
+
+    /// Latency histogram; see [`bucket_floor_ns`]
+    pub latency: [u64; LATENCY_BUCKETS],
+}
+
+/// Allocate the per-CPU tables
+pub fn init() {
+    let cpus = (percpu::num_cpus() as usize).clamp(1, SMP_MAX_CPUS);
+    for table in &TABLES[..cpus] {
+        let mut slots = Vec::with_capacity(SLOTS);
+        slots.resize_with(SLOTS, Slot::new);
+        table.store(slots.leak().as_mut_ptr(), Ordering::Release);
+    }
+
+    log_info!("syscall stats: {} CPUs", cpus);
+}
+
+/// Slot that syscall `number` is counted in
+fn slot_index(number: u32) -> usize {
+    match SyscallNumber::from_raw(number) {
+        SyscallNumber::Unknown => 0,
+        _ => number as usize,
+    }
+}
+
+/// Syscall number a slot reports as
+fn slot_number(index: usize) -> u32 {
+    match index {
+        0 => SyscallNumber::Unknown as u32,
+        _ => index as u32,
+    }
+}
+
+/// Histogram bucket for a latency of `ns`
+pub fn bucket(ns: u64) -> usize {
+    ((64 - (ns >> 10).leading_zeros()) as usize).min(LATENCY_BUCKETS - 1)
+}
+
+/// Smallest latency, in nanoseconds, that lands in `bucket`
+pub fn bucket_floor_ns(bucket: usize) -> u64 {
+    match bucket {
+        0 => 0,
+        b => 1 << (b + 9),
+    }
+}
+
+/// Record a completed call on this CPU
+#[inline]
+pub fn record(number: u32, ret: SyscallRet, ns: u64) {
+    let cpu = percpu::current_cpu_num() as usize;
+    if cpu >= SMP_MAX_CPUS {
+        return;
+    }
+
+    let table = TABLES[cpu].load(Ordering::Acquire);
+    if table.is_null() {
+        return;
+    }
+
+    let slot = unsafe { &*table.add(slot_index(number)) };
+    slot.calls.fetch_add(1, Ordering::Relaxed);
+    if ret < 0 {
+        slot.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    slot.total_ns.fetch_add(ns, Ordering::Relaxed);
+    slot.latency[bucket(ns)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Slots that are reported: unknown, then every assigned number in order
+///
+/// The set never changes, so consecutive snapshots line up.
+fn reported_slots() -> impl Iterator<Item = usize> {
+    (0..SLOTS).filter(|&index| index == 0 || slot_index(index as u32) == index)
+}
+
+/// Number of records in a snapshot
+pub fn count() -> usize {
+    reported_slots().count()
+}
+
+/// Statistics for the slot at `index`, summed across CPUs
+fn snapshot_slot(index: usize) -> SyscallStatsRecord {
+    let mut record = SyscallStatsRecord {
+        number: slot_number(index),
+        reserved: 0,
+        calls: 0,
+        errors: 0,
+        total_ns: 0,
+        latency: [0; LATENCY_BUCKETS],
+    };
+
+    for table in TABLES.iter() {
+        let table = table.load(Ordering::Acquire);
+        if table.is_null() {
+            continue;
+        }
+
+        let slot = unsafe { &*table.add(index) };
+        record.calls += slot.calls.load(Ordering::Relaxed);
+        record.errors += slot.errors.load(Ordering::Relaxed);
+        record.total_ns += slot.total_ns.load(Ordering::Relaxed);
+        for (sum, bucket) in record.latency.iter_mut().zip(slot.latency.iter()) {
+            *sum += bucket.load(Ordering::Relaxed);
+        }
+    }
+    record
+}
+
+/// Every reported syscall's statistics, in number order with unknown first
+pub fn snapshot() -> impl Iterator<Item = SyscallStatsRecord> {
+    reported_slots().map(snapshot_slot)
+}
+
+/// Statistics for syscall `number`, summed across CPUs
+pub fn get(number: u32) -> SyscallStatsRecord {
+    snapshot_slot(slot_index(number))
+}
+
+/// ============================================================================
+/// Shell Command
+/// ============================================================================
+
+fn cmd_syscalls(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    let prefixes = &argv[1..argc as usize];
+    let selected = |name: &str| prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p));
+
+    crate::println!("{:<28} {:>12} {:>10} {:>10}", "syscall", "calls", "errors", "avg ns");
+    for record in snapshot() {
+        let name = SyscallNumber::from_raw(record.number).name();
+        if record.calls == 0 || !selected(name) {
+            continue;
+        }
+        crate::println!(
+            "{:<28} {:>12} {:>10} {:>10}",
+            name,
+            record.calls,
+            record.errors,
+            record.total_ns / record.calls
+        );
+
+        // Histograms only for the syscalls asked about
+        if !prefixes.is_empty() {
+            for (bucket, &calls) in record.latency.iter().enumerate() {
+                if calls != 0 {
+                    crate::println!("    >= {:>10} ns {:>12}", bucket_floor_ns(bucket), calls);
+                }
+            }
+        }
+    }
+    0
+}
+
+crate::static_command!("syscalls", "show syscall counts and latency [prefix...]", cmd_syscalls);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_layout() {
+        assert_eq!(core::mem::size_of::<SyscallStatsRecord>(), 32 + 8 * LATENCY_BUCKETS);
+    }
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(1023), 0);
+        assert_eq!(bucket(1024), 1);
+        assert_eq!(bucket(2047), 1);
+        assert_eq!(bucket(2048), 2);
+        assert_eq!(bucket(u64::MAX), LATENCY_BUCKETS - 1);
+
+        for b in 1..LATENCY_BUCKETS {
+            assert_eq!(bucket(bucket_floor_ns(b)), b);
+            assert_eq!(bucket(bucket_floor_ns(b) - 1), b - 1);
+        }
+    }
+
+    #[test]
+    fn test_slots() {
+        assert_eq!(slot_index(0x10), 0x10);
+        assert_eq!(slot_index(0x0A), 0);
+        assert_eq!(slot_index(0x1_0010), 0);
+        assert_eq!(slot_number(0), SyscallNumber::Unknown as u32);
+        assert_eq!(slot_number(0xF2), 0xF2);
+
+        let slots: Vec<usize> = reported_slots().collect();
+        assert_eq!(slots[0], 0);
+        assert!(slots.contains(&0x07));
+        assert!(!slots.contains(&0x0A));
+        assert_eq!(count(), slots.len());
+    }
+}
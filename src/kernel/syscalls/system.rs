@@ -15,6 +15,15 @@
 //! - `rx_system_powerctl` - Power control operations
 //! - `rx_system_mexec_payload_get` - Get mexec boot data
 //! - `rx_system_mexec` - Execute a new kernel
+//! - `rx_system_crashlog_read` - Read the crash log left by the previous boot
+//! - `rx_system_get_event` - Get a memory pressure event
+//! - `rx_system_get_version` - Get the ABI version and kernel build
+//! - `rx_system_get_features` - Get feature bits
+//! - `rx_system_get_phys_mem` - Get the amount of physical memory
+//! - `rx_system_get_num_cpus` - Get the number of online CPUs
+//! - `rx_efi_get_variable` - Read an EFI variable
+//! - `rx_efi_set_variable` - Write or delete an EFI variable
+//! - `rx_efi_get_time` - Read the EFI real-time clock
 //!
 //! # Design
 //!
@@ -24,9 +33,17 @@
 //! - Memory execution for kernel updates
 
 
+use crate::kernel::lib::crashlog;
+use crate::kernel::lib::efi;
+use crate::kernel::lib::version;
+use crate::kernel::percpu;
+use crate::platform;
 use crate::kernel::object::{Handle, HandleTable, ObjectType, Rights};
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
-use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::{SyscallNumber, SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::resource::{self, resource_kind};
+use crate::kernel::vm::pmm;
+use crate::kernel::vm::pressure::{self, PressureLevel};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
@@ -70,17 +87,17 @@ pub enum PowerctlCmd {
 
 impl PowerctlCmd {
     /// Create from raw value
-    pub const fn from_raw(raw: u32) -> Self {
+    pub const fn from_raw(raw: u32) -> Option<Self> {
         match raw {
-            1 => Self::EnableAllCpus,
-            2 => Self::DisableAllCpusButPrimary,
-            3 => Self::AcpiTransitionSState,
-            4 => Self::X86SetPkgPl1,
-            5 => Self::Reboot,
-            6 => Self::RebootBootloader,
-            7 => Self::RebootRecovery,
-            8 => Self::Shutdown,
-            _ => Self::Reboot, // Default to reboot
+            1 => Some(Self::EnableAllCpus),
+            2 => Some(Self::DisableAllCpusButPrimary),
+            3 => Some(Self::AcpiTransitionSState),
+            4 => Some(Self::X86SetPkgPl1),
+            5 => Some(Self::Reboot),
+            6 => Some(Self::RebootBootloader),
+            7 => Some(Self::RebootRecovery),
+            8 => Some(Self::Shutdown),
+            _ => None,
         }
     }
 
@@ -88,6 +105,61 @@ impl PowerctlCmd {
     pub const fn into_raw(self) -> u32 {
         self as u32
     }
+
+    /// Halt action that carries out a reboot or shutdown command
+    pub const fn halt_action(self) -> Option<u32> {
+        match self {
+            Self::Reboot => Some(platform::HALT_ACTION_REBOOT),
+            Self::RebootBootloader => Some(platform::HALT_ACTION_REBOOT_BOOTLOADER),
+            Self::RebootRecovery => Some(platform::HALT_ACTION_REBOOT_RECOVERY),
+            Self::Shutdown => Some(platform::HALT_ACTION_SHUTDOWN),
+            _ => None,
+        }
+    }
+}
+
+/// ============================================================================
+/// System Events
+/// ============================================================================
+
+/// Events returned by `rx_system_get_event`
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemEvent {
+    /// Signaled while memory is out and jobs are being killed
+    OutOfMemory = 1,
+
+    /// Signaled while memory pressure is critical
+    MemoryPressureCritical = 2,
+
+    /// Signaled while memory pressure is at warning level
+    MemoryPressureWarning = 3,
+
+    /// Signaled while memory pressure is normal
+    MemoryPressureNormal = 4,
+}
+
+impl SystemEvent {
+    /// Create from raw value
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::OutOfMemory),
+            2 => Some(Self::MemoryPressureCritical),
+            3 => Some(Self::MemoryPressureWarning),
+            4 => Some(Self::MemoryPressureNormal),
+            _ => None,
+        }
+    }
+
+    /// Pressure level whose event this is
+    pub const fn pressure_level(self) -> PressureLevel {
+        match self {
+            Self::OutOfMemory => PressureLevel::OutOfMemory,
+            Self::MemoryPressureCritical => PressureLevel::Critical,
+            Self::MemoryPressureWarning => PressureLevel::Warning,
+            Self::MemoryPressureNormal => PressureLevel::Normal,
+        }
+    }
 }
 
 /// ============================================================================
@@ -139,11 +211,31 @@ fn get_system_metrics() -> SystemMetrics {
 /// ============================================================================
 
 /// Resource kind
+///
+/// Values match [`resource_kind`].
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResourceKind {
     /// Root resource
-    Root = 0,
+    Root = resource_kind::ROOT,
+
+    /// Interrupt vectors
+    Irq = resource_kind::IRQ,
+
+    /// Physical address range
+    Mmio = resource_kind::MMIO,
+
+    /// System resource
+    System = resource_kind::SYSTEM,
+
+    /// Hypervisor resource
+    Hypervisor = resource_kind::HYPERVISOR,
+
+    /// x86 I/O ports
+    IoPort = resource_kind::IOPORT,
+
+    /// EFI runtime services
+    Efi = resource_kind::EFI,
 
     /// Invalid resource
     Invalid = 0xFFFF,
@@ -151,6 +243,9 @@ pub enum ResourceKind {
 
 /// Validate a resource handle
 ///
+/// The root resource is valid for every kind; a derived resource only for
+/// its own kind, and never for `Root`.
+///
 /// # Arguments
 ///
 /// * `handle` - Resource handle value
@@ -160,17 +255,39 @@ pub enum ResourceKind {
 ///
 /// - Ok(()) if handle is valid
 /// - Err(RX_ERR_ACCESS_DENIED) if handle is invalid
-fn validate_resource(handle: u32, expected_kind: ResourceKind) -> Result {
-    // TODO: Implement proper handle validation
-    // For now, only handle 0 (root resource) is valid
-    if handle != 0 {
-        return Err(RX_ERR_ACCESS_DENIED);
+pub(crate) fn validate_resource(handle: u32, expected_kind: ResourceKind) -> Result {
+    if expected_kind == ResourceKind::Invalid {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    if resource::is_root(handle) {
+        return Ok(());
     }
 
-    match expected_kind {
-        ResourceKind::Root => Ok(()),
-        _ => Err(RX_ERR_INVALID_ARGS),
+    match resource::lookup(handle) {
+        Some(rsrc) if expected_kind != ResourceKind::Root && rsrc.kind() == expected_kind as u32 => {
+            Ok(())
+        }
+        _ => Err(RX_ERR_ACCESS_DENIED),
+    }
+}
+
+/// Validate a resource handle for `[base, base + size)` of a ranged kind
+///
+/// # Returns
+///
+/// - Ok(()) if the root resource or a resource of `kind` covers the range
+/// - Err(RX_ERR_ACCESS_DENIED) if handle is not such a resource
+/// - Err(RX_ERR_OUT_OF_RANGE) if the resource doesn't cover the range
+pub(crate) fn validate_ranged_resource(
+    handle: u32,
+    kind: ResourceKind,
+    base: u64,
+    size: u64,
+) -> Result {
+    if !resource_kind::is_ranged(kind as u32) {
+        return Err(RX_ERR_INVALID_ARGS);
     }
+    resource::validate(handle, kind as u32, base, size)
 }
 
 /// ============================================================================
@@ -217,6 +334,11 @@ pub fn sys_system_get_metrics_impl(metrics_out: usize) -> SyscallRet {
 
 /// Power control syscall handler
 ///
+/// Reboot, reboot to bootloader or recovery, and shutdown don't return on
+/// success: userspace must have flushed anything it cares about. They use
+/// PSCI on arm64, the ACPI reset register or S5 on x86_64 and SBI system
+/// reset on riscv64; a platform that can't power off halts instead.
+///
 /// # Arguments
 ///
 /// * `resource_handle` - Root resource handle
@@ -225,7 +347,9 @@ pub fn sys_system_get_metrics_impl(metrics_out: usize) -> SyscallRet {
 ///
 /// # Returns
 ///
-/// * On success: 0
+/// * On success: 0, for commands that return
+/// * RX_ERR_ACCESS_DENIED if `resource_handle` is not the root resource
+/// * RX_ERR_INVALID_ARGS for an unknown command
 /// * On error: Negative error code
 pub fn sys_system_powerctl_impl(resource_handle: u32, cmd: u32, arg: usize) -> SyscallRet {
     log_debug!(
@@ -239,7 +363,15 @@ pub fn sys_system_powerctl_impl(resource_handle: u32, cmd: u32, arg: usize) -> S
         return err_to_ret(err);
     }
 
-    let command = PowerctlCmd::from_raw(cmd);
+    let command = match PowerctlCmd::from_raw(cmd) {
+        Some(command) => command,
+        None => return err_to_ret(RX_ERR_INVALID_ARGS),
+    };
+
+    if let Some(action) = command.halt_action() {
+        log_info!("Power: {:?} requested by userspace", command);
+        platform::platform_halt(platform::HALT_REASON_USER_REQUEST, action);
+    }
 
     match command {
         PowerctlCmd::EnableAllCpus => {
@@ -256,30 +388,6 @@ pub fn sys_system_powerctl_impl(resource_handle: u32, cmd: u32, arg: usize) -> S
             ok_to_ret(0)
         }
 
-        PowerctlCmd::Reboot => {
-            log_info!("Power: Rebooting system...");
-            // TODO: Implement graceful reboot
-            ok_to_ret(0)
-        }
-
-        PowerctlCmd::RebootBootloader => {
-            log_info!("Power: Rebooting to bootloader...");
-            // TODO: Implement bootloader reboot
-            ok_to_ret(0)
-        }
-
-        PowerctlCmd::RebootRecovery => {
-            log_info!("Power: Rebooting to recovery...");
-            // TODO: Implement recovery reboot
-            ok_to_ret(0)
-        }
-
-        PowerctlCmd::Shutdown => {
-            log_info!("Power: Shutting down system...");
-            // TODO: Implement graceful shutdown
-            ok_to_ret(0)
-        }
-
         PowerctlCmd::AcpiTransitionSState => {
             // Read S-state from user argument
             let user_ptr = UserPtr::<u8>::new(arg);
@@ -313,6 +421,11 @@ pub fn sys_system_powerctl_impl(resource_handle: u32, cmd: u32, arg: usize) -> S
             // TODO: Implement x86 power limit
             ok_to_ret(0)
         }
+
+        PowerctlCmd::Reboot
+        | PowerctlCmd::RebootBootloader
+        | PowerctlCmd::RebootRecovery
+        | PowerctlCmd::Shutdown => unreachable!("handled by platform_halt"),
     }
 }
 
@@ -432,6 +545,481 @@ pub fn sys_system_mexec_impl(
     ok_to_ret(0)
 }
 
+/// ============================================================================
+/// Syscall: System Crashlog Read
+/// ============================================================================
+
+/// Read the crash log recovered from the previous boot
+///
+/// The log is read like a file: callers loop, advancing `offset` by the
+/// count written to `actual_out`, until it reads 0 bytes.
+///
+/// # Arguments
+///
+/// * `resource_handle` - Root resource handle
+/// * `offset` - Byte offset into the log
+/// * `buffer` - User buffer
+/// * `len` - Size of `buffer`
+/// * `actual_out` - User pointer to store the number of bytes read
+///
+/// # Returns
+///
+/// * On success: 0
+/// * RX_ERR_NOT_FOUND if the previous boot left no crash log
+/// * On error: Negative error code
+pub fn sys_system_crashlog_read_impl(
+    resource_handle: u32,
+    offset: usize,
+    buffer: usize,
+    len: usize,
+    actual_out: usize,
+) -> SyscallRet {
+    log_debug!(
+        "sys_system_crashlog_read: resource={:#x} offset={} len={}",
+        resource_handle, offset, len
+    );
+
+    // Validate root resource
+    if let Err(err) = validate_resource(resource_handle, ResourceKind::Root) {
+        log_error!("sys_system_crashlog_read: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let log = match crashlog::recovered() {
+        Some(log) => log,
+        None => return err_to_ret(RX_ERR_NOT_FOUND),
+    };
+
+    let start = offset.min(log.text.len());
+    let count = len.min(log.text.len() - start);
+
+    if count > 0 {
+        let user_ptr = UserPtr::<u8>::new(buffer);
+        unsafe {
+            if let Err(err) = copy_to_user(user_ptr, log.text[start..].as_ptr(), count) {
+                log_error!("sys_system_crashlog_read: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
+
+    if actual_out != 0 {
+        let user_ptr = UserPtr::<u8>::new(actual_out);
+        unsafe {
+            if let Err(err) = copy_to_user(
+                user_ptr,
+                &count as *const usize as *const u8,
+                core::mem::size_of::<usize>(),
+            ) {
+                log_error!("sys_system_crashlog_read: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: System Get Event
+/// ============================================================================
+
+/// Get a system event syscall handler
+///
+/// Each memory pressure level has an event that is signaled while the
+/// system is at that level, so a waiter learns when the level changes.
+///
+/// # Arguments
+///
+/// * `resource_handle` - Root resource handle
+/// * `kind` - Raw `SystemEvent` value
+///
+/// # Returns
+///
+/// * On success: Handle value for the event (WAIT right only)
+/// * RX_ERR_BAD_STATE if memory pressure tracking hasn't started
+/// * On error: Negative error code
+pub fn sys_system_get_event_impl(resource_handle: u32, kind: u32) -> SyscallRet {
+    log_debug!("sys_system_get_event: resource={:#x} kind={}", resource_handle, kind);
+
+    // Validate root resource
+    if let Err(err) = validate_resource(resource_handle, ResourceKind::Root) {
+        log_error!("sys_system_get_event: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let event = match SystemEvent::from_raw(kind) {
+        Some(event) => event,
+        None => {
+            log_error!("sys_system_get_event: invalid kind {}", kind);
+            return err_to_ret(RX_ERR_INVALID_ARGS);
+        }
+    };
+
+    match pressure::event_handle(event.pressure_level()) {
+        Some(handle) => ok_to_ret(handle as usize),
+        None => err_to_ret(RX_ERR_BAD_STATE),
+    }
+}
+
+/// ============================================================================
+/// Syscalls: Version and Features
+/// ============================================================================
+
+/// Version information returned by `rx_system_get_version`
+///
+/// Strings are UTF-8, NUL-padded.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SystemVersion {
+    /// Syscall ABI version
+    pub abi_version: u32,
+
+    /// Reserved, zero
+    pub reserved: u32,
+
+    /// Kernel version
+    pub kernel_version: [u8; 32],
+
+    /// Kernel build ID
+    pub build_id: [u8; 64],
+}
+
+/// Feature kinds for `rx_system_get_features`
+pub mod feature_kind {
+    /// Bitmap of implemented syscalls: bit `n % 64` of word `n / 64` is set
+    /// if syscall `n` is implemented
+    pub const SYSCALLS: u32 = 0;
+}
+
+/// Size of the `feature_kind::SYSCALLS` bitmap, covering numbers 0-0xFF
+pub const SYSCALL_BITMAP_SIZE: usize = 32;
+
+/// Copy `s` into a NUL-padded buffer, truncating if needed
+fn padded<const N: usize>(s: &str) -> [u8; N] {
+    let mut buf = [0u8; N];
+    let len = s.len().min(N);
+    buf[..len].copy_from_slice(&s.as_bytes()[..len]);
+    buf
+}
+
+/// Bitmap of the syscalls this kernel implements
+fn syscall_bitmap() -> [u64; SYSCALL_BITMAP_SIZE / 8] {
+    let mut words = [0u64; SYSCALL_BITMAP_SIZE / 8];
+    for n in 0..(SYSCALL_BITMAP_SIZE * 8) as u32 {
+        if SyscallNumber::from_raw(n) != SyscallNumber::Unknown {
+            words[n as usize / 64] |= 1 << (n % 64);
+        }
+    }
+    words
+}
+
+/// Get version information syscall handler
+///
+/// # Arguments
+///
+/// * `version_out` - User pointer to a `SystemVersion`
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_system_get_version_impl(version_out: usize) -> SyscallRet {
+    let info = SystemVersion {
+        abi_version: SyscallNumber::ABI_VERSION,
+        reserved: 0,
+        kernel_version: padded(version::kernel_version()),
+        build_id: padded(version::build_id()),
+    };
+
+    unsafe {
+        if let Err(err) = copy_to_user(
+            UserPtr::new(version_out),
+            &info as *const SystemVersion as *const u8,
+            core::mem::size_of::<SystemVersion>(),
+        ) {
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// Get feature bits syscall handler
+///
+/// # Arguments
+///
+/// * `kind` - Feature kind, from [`feature_kind`]
+/// * `buffer` - User buffer for the features
+/// * `buffer_size` - Size of `buffer` in bytes
+///
+/// # Returns
+///
+/// * On success: 0
+/// * RX_ERR_INVALID_ARGS for an unknown kind
+/// * RX_ERR_BUFFER_TOO_SMALL if `buffer` can't hold the features
+/// * On error: Negative error code
+pub fn sys_system_get_features_impl(kind: u32, buffer: usize, buffer_size: usize) -> SyscallRet {
+    let words = match kind {
+        feature_kind::SYSCALLS => syscall_bitmap(),
+        _ => return err_to_ret(RX_ERR_INVALID_ARGS),
+    };
+    if buffer_size < SYSCALL_BITMAP_SIZE {
+        return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+    }
+
+    unsafe {
+        if let Err(err) = copy_to_user(
+            UserPtr::new(buffer),
+            words.as_ptr() as *const u8,
+            SYSCALL_BITMAP_SIZE,
+        ) {
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// Get physical memory size syscall handler
+///
+/// # Returns
+///
+/// Total physical memory in bytes
+pub fn sys_system_get_phys_mem_impl() -> SyscallRet {
+    ok_to_ret(pmm::pmm_count_total_bytes() as usize)
+}
+
+/// Get CPU count syscall handler
+///
+/// # Returns
+///
+/// Number of online CPUs
+pub fn sys_system_get_num_cpus_impl() -> SyscallRet {
+    ok_to_ret(percpu::num_cpus() as usize)
+}
+
+/// ============================================================================
+/// Syscalls: EFI Runtime Services
+/// ============================================================================
+
+/// EFI variable named by `rx_efi_get_variable` and `rx_efi_set_variable`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EfiVariable {
+    /// Vendor GUID in EFI byte order
+    pub vendor: [u8; 16],
+
+    /// EFI_VARIABLE_* attributes: stored by get, passed to set
+    pub attributes: u32,
+
+    /// Length of `name` in UCS-2 characters, without a NUL
+    pub name_len: u32,
+
+    /// User pointer to the UCS-2 name
+    pub name: u64,
+}
+
+/// Copy an `EfiVariable` and its name from userspace
+///
+/// Returns the variable and the length of the name copied into `name`.
+fn copy_efi_variable_from_user(
+    variable: usize,
+    name: &mut [u16; efi::VARIABLE_NAME_MAX],
+) -> Result<(EfiVariable, usize)> {
+    let mut var = EfiVariable::default();
+    unsafe {
+        copy_from_user(
+            &mut var as *mut EfiVariable as *mut u8,
+            UserPtr::<u8>::new(variable),
+            core::mem::size_of::<EfiVariable>(),
+        )?;
+    }
+
+    let len = var.name_len as usize;
+    if len == 0 || len > efi::VARIABLE_NAME_MAX {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    unsafe {
+        copy_from_user(
+            name.as_mut_ptr() as *mut u8,
+            UserPtr::<u8>::new(var.name as usize),
+            len * core::mem::size_of::<u16>(),
+        )?;
+    }
+    Ok((var, len))
+}
+
+/// Read an EFI variable syscall handler
+///
+/// # Arguments
+///
+/// * `resource_handle` - EFI or root resource handle
+/// * `variable` - User pointer to an `EfiVariable`; its `attributes` are
+///   stored on success
+/// * `data` - User buffer for the value
+/// * `data_size` - Size of `data`
+/// * `actual_out` - User pointer to store the size of the value
+///
+/// # Returns
+///
+/// * On success: 0
+/// * RX_ERR_BUFFER_TOO_SMALL if `data` is too small; `actual_out` holds
+///   the size needed
+/// * RX_ERR_NOT_FOUND if the variable doesn't exist
+/// * RX_ERR_NOT_SUPPORTED without runtime services
+/// * On error: Negative error code
+pub fn sys_efi_get_variable_impl(
+    resource_handle: u32,
+    variable: usize,
+    data: usize,
+    data_size: usize,
+    actual_out: usize,
+) -> SyscallRet {
+    log_debug!("sys_efi_get_variable: resource={:#x} size={}", resource_handle, data_size);
+
+    if let Err(err) = validate_resource(resource_handle, ResourceKind::Efi) {
+        log_error!("sys_efi_get_variable: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let mut name = [0u16; efi::VARIABLE_NAME_MAX];
+    let (var, name_len) = match copy_efi_variable_from_user(variable, &mut name) {
+        Ok(copied) => copied,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let mut buf = alloc::vec![0u8; data_size.min(efi::VARIABLE_DATA_MAX)];
+    let mut size = 0usize;
+    let result = efi::get_variable(&name[..name_len], &var.vendor, &mut buf, &mut size);
+
+    if matches!(result, Ok(_) | Err(RX_ERR_BUFFER_TOO_SMALL)) && actual_out != 0 {
+        unsafe {
+            if let Err(err) = copy_to_user(
+                UserPtr::<u8>::new(actual_out),
+                &size as *const usize as *const u8,
+                core::mem::size_of::<usize>(),
+            ) {
+                log_error!("sys_efi_get_variable: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+    }
+
+    let attributes = match result {
+        Ok(attributes) => attributes,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let attributes_ptr = variable + core::mem::offset_of!(EfiVariable, attributes);
+    unsafe {
+        if let Err(err) = copy_to_user(UserPtr::<u8>::new(data), buf.as_ptr(), size) {
+            log_error!("sys_efi_get_variable: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+        if let Err(err) = copy_to_user(
+            UserPtr::<u8>::new(attributes_ptr),
+            &attributes as *const u32 as *const u8,
+            core::mem::size_of::<u32>(),
+        ) {
+            log_error!("sys_efi_get_variable: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// Write an EFI variable syscall handler
+///
+/// A zero `data_size` deletes the variable.
+///
+/// # Arguments
+///
+/// * `resource_handle` - EFI or root resource handle
+/// * `variable` - User pointer to an `EfiVariable`
+/// * `data` - User pointer to the value
+/// * `data_size` - Size of the value, at most 32 KiB
+///
+/// # Returns
+///
+/// * On success: 0
+/// * RX_ERR_ACCESS_DENIED if the firmware refuses the write
+/// * RX_ERR_NOT_SUPPORTED without runtime services
+/// * On error: Negative error code
+pub fn sys_efi_set_variable_impl(
+    resource_handle: u32,
+    variable: usize,
+    data: usize,
+    data_size: usize,
+) -> SyscallRet {
+    log_debug!("sys_efi_set_variable: resource={:#x} size={}", resource_handle, data_size);
+
+    if let Err(err) = validate_resource(resource_handle, ResourceKind::Efi) {
+        log_error!("sys_efi_set_variable: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+    if data_size > efi::VARIABLE_DATA_MAX {
+        return err_to_ret(RX_ERR_OUT_OF_RANGE);
+    }
+
+    let mut name = [0u16; efi::VARIABLE_NAME_MAX];
+    let (var, name_len) = match copy_efi_variable_from_user(variable, &mut name) {
+        Ok(copied) => copied,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let mut buf = alloc::vec![0u8; data_size];
+    unsafe {
+        if let Err(err) = copy_from_user(buf.as_mut_ptr(), UserPtr::<u8>::new(data), data_size) {
+            log_error!("sys_efi_set_variable: copy_from_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    match efi::set_variable(&name[..name_len], &var.vendor, var.attributes, &buf) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// Read the EFI real-time clock syscall handler
+///
+/// # Arguments
+///
+/// * `resource_handle` - EFI or root resource handle
+/// * `time_out` - User pointer to store an `EfiTime`
+///
+/// # Returns
+///
+/// * On success: 0
+/// * RX_ERR_NOT_SUPPORTED without runtime services
+/// * On error: Negative error code
+pub fn sys_efi_get_time_impl(resource_handle: u32, time_out: usize) -> SyscallRet {
+    if let Err(err) = validate_resource(resource_handle, ResourceKind::Efi) {
+        log_error!("sys_efi_get_time: invalid resource: {:?}", err);
+        return err_to_ret(err);
+    }
+
+    let time = match efi::get_time() {
+        Ok(time) => time,
+        Err(err) => return err_to_ret(err),
+    };
+
+    unsafe {
+        if let Err(err) = copy_to_user(
+            UserPtr::<u8>::new(time_out),
+            &time as *const efi::EfiTime as *const u8,
+            core::mem::size_of::<efi::EfiTime>(),
+        ) {
+            log_error!("sys_efi_get_time: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
 /// ============================================================================
 /// Module Statistics
 /// ============================================================================
@@ -479,20 +1067,81 @@ mod tests {
     #[test]
     fn test_powerctl_cmd() {
         let cmd = PowerctlCmd::Reboot;
-        assert_eq!(PowerctlCmd::from_raw(5), PowerctlCmd::Reboot);
+        assert_eq!(PowerctlCmd::from_raw(5), Some(PowerctlCmd::Reboot));
         assert_eq!(cmd.into_raw(), 5);
+        assert_eq!(PowerctlCmd::from_raw(0), None);
+        assert_eq!(PowerctlCmd::from_raw(9), None);
+
+        assert_eq!(PowerctlCmd::Shutdown.halt_action(), Some(platform::HALT_ACTION_SHUTDOWN));
+        assert_eq!(
+            PowerctlCmd::RebootBootloader.halt_action(),
+            Some(platform::HALT_ACTION_REBOOT_BOOTLOADER)
+        );
+        assert_eq!(PowerctlCmd::EnableAllCpus.halt_action(), None);
+    }
+
+    #[test]
+    fn test_syscall_bitmap() {
+        let words = syscall_bitmap();
+        let implemented = |n: u32| words[n as usize / 64] & (1 << (n % 64)) != 0;
+
+        assert!(implemented(0x01));
+        assert!(implemented(0xA0));
+        assert!(implemented(0xF2));
+        // Reserved and unassigned
+        assert!(!implemented(0x70));
+        assert!(!implemented(0xFF));
+
+        assert_eq!(padded::<4>("0.1.0"), *b"0.1.");
+        assert_eq!(padded::<8>("0.1"), *b"0.1\0\0\0\0\0");
     }
 
     #[test]
     fn test_validate_resource() {
-        // Root resource (handle 0)
-        assert!(validate_resource(0, ResourceKind::Root).is_ok());
+        let root = resource::test_root_handle();
+        assert!(validate_resource(root, ResourceKind::Root).is_ok());
+        assert!(validate_resource(root, ResourceKind::Mmio).is_ok());
 
         // Invalid handle
         assert!(validate_resource(999, ResourceKind::Root).is_err());
 
         // Wrong kind
-        assert!(validate_resource(0, ResourceKind::Invalid).is_err());
+        assert!(validate_resource(root, ResourceKind::Invalid).is_err());
+    }
+
+    #[test]
+    fn test_validate_ranged_resource() {
+        let root = resource::test_root_handle();
+        let handle = resource::create(root, resource_kind::MMIO, 0, 0xfe00_0000, 0x1000, "mmio".into())
+            .unwrap();
+
+        assert!(validate_ranged_resource(handle, ResourceKind::Mmio, 0xfe00_0000, 0x1000).is_ok());
+        assert_eq!(
+            validate_ranged_resource(handle, ResourceKind::Mmio, 0xfe00_0800, 0x1000),
+            Err(RX_ERR_OUT_OF_RANGE)
+        );
+        assert_eq!(
+            validate_ranged_resource(handle, ResourceKind::Irq, 0, 1),
+            Err(RX_ERR_ACCESS_DENIED)
+        );
+
+        // A derived resource is never the root
+        assert_eq!(validate_resource(handle, ResourceKind::Root), Err(RX_ERR_ACCESS_DENIED));
+        assert!(validate_resource(handle, ResourceKind::Mmio).is_ok());
+    }
+
+    #[test]
+    fn test_efi_resource() {
+        assert_eq!(core::mem::size_of::<EfiVariable>(), 32);
+
+        let root = resource::test_root_handle();
+        let handle = resource::create(root, resource_kind::EFI, 0, 0, 0, "efi".into()).unwrap();
+        assert!(validate_resource(handle, ResourceKind::Efi).is_ok());
+        assert_eq!(validate_resource(handle, ResourceKind::System), Err(RX_ERR_ACCESS_DENIED));
+
+        // No runtime services without a UEFI boot
+        assert_eq!(sys_efi_get_time_impl(handle, 0), err_to_ret(RX_ERR_NOT_SUPPORTED));
+        assert_eq!(sys_efi_get_time_impl(999, 0), err_to_ret(RX_ERR_ACCESS_DENIED));
     }
 
     #[test]
@@ -504,15 +1153,33 @@ mod tests {
 
     #[test]
     fn test_powerctl_validation() {
-        // Invalid resource handle
+        // Invalid resource handle, even for a command that would not return
         let result = sys_system_powerctl_impl(999, PowerctlCmd::Reboot as u32, 0);
-        assert!(result < 0);
+        assert_eq!(result, err_to_ret(RX_ERR_ACCESS_DENIED));
 
-        // Valid reboot
-        let result = sys_system_powerctl_impl(0, PowerctlCmd::Reboot as u32, 0);
+        // Unknown command
+        let root = resource::test_root_handle();
+        let result = sys_system_powerctl_impl(root, 0x999, 0);
+        assert_eq!(result, err_to_ret(RX_ERR_INVALID_ARGS));
+
+        // Valid command that returns
+        let result = sys_system_powerctl_impl(root, PowerctlCmd::EnableAllCpus as u32, 0);
         assert!(result >= 0);
     }
 
+    #[test]
+    fn test_get_event_validation() {
+        assert_eq!(sys_system_get_event_impl(999, 1), err_to_ret(RX_ERR_ACCESS_DENIED));
+        let root = resource::test_root_handle();
+        assert_eq!(sys_system_get_event_impl(root, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_system_get_event_impl(root, 5), err_to_ret(RX_ERR_INVALID_ARGS));
+
+        assert_eq!(
+            SystemEvent::from_raw(2).map(SystemEvent::pressure_level),
+            Some(PressureLevel::Critical)
+        );
+    }
+
     #[test]
     fn test_mexec_payload_get_validation() {
         // Invalid resource handle
@@ -520,11 +1187,12 @@ mod tests {
         assert!(result < 0);
 
         // Buffer too large
-        let result = sys_system_mexec_payload_get_impl(0, 0, BOOTDATA_PLATFORM_EXTRA_BYTES + 1);
+        let root = resource::test_root_handle();
+        let result = sys_system_mexec_payload_get_impl(root, 0, BOOTDATA_PLATFORM_EXTRA_BYTES + 1);
         assert!(result < 0);
 
         // Valid call
-        let result = sys_system_mexec_payload_get_impl(0, 0, 4096);
+        let result = sys_system_mexec_payload_get_impl(root, 0, 4096);
         assert!(result >= 0);
     }
 
@@ -535,7 +1203,7 @@ mod tests {
         assert!(result < 0);
 
         // Valid call (won't actually mexec)
-        let result = sys_system_mexec_impl(0, 1, 2);
+        let result = sys_system_mexec_impl(resource::test_root_handle(), 1, 2);
         assert!(result >= 0);
     }
 }
@@ -17,8 +17,15 @@
 //! - `rx_process_create` - Create a process
 //! - `rx_process_start` - Start a process
 //! - `rx_process_exit` - Exit current process
+//! - `rx_process_read_memory` - Read another process's memory
+//! - `rx_process_write_memory` - Write another process's memory
 //! - `rx_task_kill` - Kill a task (thread or process)
 //! - `rx_job_create` - Create a job
+//! - `rx_thread_read_state` - Read a thread's state at its exception
+//! - `rx_thread_write_state` - Set a thread's hardware breakpoints
+//! - `rx_thread_resume` - Release a thread from its exception
+//! - `rx_thread_write_coredump` - Dump a thread's process at its exception
+//! - `rx_task_create_exception_channel` - Bind an exception channel to a job
 //!
 //! # Design
 //!
@@ -28,17 +35,21 @@
 //! - Proper cleanup on errors
 
 
+use crate::kernel::cmdline;
+use crate::kernel::object::coredump;
+use crate::kernel::object::exception::{
+    self, DebugRegs, GeneralRegs, THREAD_STATE_DEBUG_REGS, THREAD_STATE_GENERAL_REGS, THREAD_STATE_STACK,
+};
 use crate::kernel::object::job::{self, Job, JobId};
 use crate::kernel::process::{self, HandleRights, ObjectType, Process, ProcessFlags};
-use crate::kernel::thread::{self, Thread, ThreadId};
+use crate::kernel::thread::{self, Thread, ThreadId, ThreadRef};
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
-use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
+use crate::kernel::syscalls::{handle_ops, vmar, vmo, SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
 use alloc::string::String;
 use crate::kernel::sync::Mutex;
-use alloc::sync::Arc;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 // Import logging macros
@@ -57,7 +68,7 @@ const MAX_THREADS: usize = 65536;
 /// Maps thread IDs to thread objects.
 struct ThreadRegistry {
     /// Thread entries (simplified - using array for now)
-    threads: Mutex<[Option<Arc<Thread>>; MAX_THREADS]>,
+    threads: Mutex<[Option<ThreadRef>; MAX_THREADS]>,
 
     /// Next thread index to allocate
     next_index: AtomicUsize,
@@ -69,7 +80,7 @@ struct ThreadRegistry {
 impl ThreadRegistry {
     /// Create a new thread registry
     const fn new() -> Self {
-        const INIT: Option<Arc<Thread>> = None;
+        const INIT: Option<ThreadRef> = None;
 
         Self {
             threads: Mutex::new([INIT; MAX_THREADS]),
@@ -79,7 +90,7 @@ impl ThreadRegistry {
     }
 
     /// Insert a thread into the registry
-    pub fn insert(&mut self, thread: Arc<Thread>) -> Result<ThreadId> {
+    pub fn insert(&mut self, thread: ThreadRef) -> Result<ThreadId> {
         let tid = thread.tid();
 
         // Find a free slot
@@ -109,7 +120,7 @@ impl ThreadRegistry {
     }
 
     /// Get a thread from the registry
-    pub fn get(&self, tid: ThreadId) -> Option<Arc<Thread>> {
+    pub fn get(&self, tid: ThreadId) -> Option<ThreadRef> {
         if tid == 0 {
             return None;
         }
@@ -133,6 +144,11 @@ unsafe impl Sync for ThreadRegistry {}
 /// Global thread registry
 static mut THREAD_REGISTRY: ThreadRegistry = ThreadRegistry::new();
 
+/// Look up a thread created by `rx_thread_create`, or a kernel thread
+pub fn lookup_thread(tid: ThreadId) -> Option<ThreadRef> {
+    unsafe { THREAD_REGISTRY.get(tid) }.or_else(|| thread::get_thread_by_id(tid))
+}
+
 /// ============================================================================
 /// Syscall: Thread Create
 /// ============================================================================
@@ -214,8 +230,8 @@ pub fn sys_thread_create_impl(
 
     log_debug!("sys_thread_create: created thread tid={}", thread.tid());
 
-    // Wrap in Arc for registry
-    let thread_arc = Arc::new(thread);
+    // Move into the thread cache for the registry
+    let thread_arc = thread.into_ref();
 
     // Insert into thread registry
     let tid = unsafe { match THREAD_REGISTRY.insert(thread_arc.clone()) {
@@ -271,7 +287,7 @@ pub fn sys_thread_start_impl(
         Some(t) => t,
         None => {
             log_error!("sys_thread_start: thread not found");
-            return err_to_ret(RX_ERR_INVALID_ARGS);
+            return err_to_ret(RX_ERR_BAD_HANDLE);
         }
     };
 
@@ -321,6 +337,265 @@ pub fn sys_thread_exit_impl(exit_code: i64) -> SyscallRet {
     ok_to_ret(0)
 }
 
+/// ============================================================================
+/// Syscall: Thread Affinity
+/// ============================================================================
+
+/// Set thread CPU affinity syscall handler
+///
+/// # Arguments
+///
+/// * `thread_handle` - Handle to the thread
+/// * `mask` - CPUs the thread may run on, bit N for CPU N
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_thread_set_affinity_impl(thread_handle: u32, mask: u64) -> SyscallRet {
+    log_debug!("sys_thread_set_affinity: handle={:#x} mask={:#x}", thread_handle, mask);
+
+    let thread = match lookup_thread(thread_handle as ThreadId) {
+        Some(t) => t,
+        None => {
+            log_error!("sys_thread_set_affinity: thread not found");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+    };
+
+    match crate::kernel::sched::set_affinity(&thread, mask) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => {
+            log_error!("sys_thread_set_affinity: no online CPU in {:#x}", mask);
+            err_to_ret(err)
+        }
+    }
+}
+
+/// Get thread CPU affinity syscall handler
+///
+/// # Arguments
+///
+/// * `thread_handle` - Handle to the thread
+/// * `mask_out` - User pointer to store the affinity mask
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_thread_get_affinity_impl(thread_handle: u32, mask_out: usize) -> SyscallRet {
+    log_debug!("sys_thread_get_affinity: handle={:#x}", thread_handle);
+
+    let thread = match lookup_thread(thread_handle as ThreadId) {
+        Some(t) => t,
+        None => {
+            log_error!("sys_thread_get_affinity: thread not found");
+            return err_to_ret(RX_ERR_BAD_HANDLE);
+        }
+    };
+
+    let mask = thread.cpu_affinity();
+    unsafe {
+        if let Err(err) = copy_to_user(
+            UserPtr::<u8>::new(mask_out),
+            &mask as *const u64 as *const u8,
+            core::mem::size_of::<u64>(),
+        ) {
+            log_error!("sys_thread_get_affinity: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(0)
+}
+
+/// ============================================================================
+/// Syscall: Thread State
+/// ============================================================================
+
+/// Read thread state syscall handler
+///
+/// Debug registers can be read at any time; otherwise only a thread
+/// waiting for its exception handler has state to read.
+///
+/// # Arguments
+///
+/// * `thread_handle` - Handle to the thread
+/// * `kind` - `THREAD_STATE_GENERAL_REGS`, `THREAD_STATE_STACK` or
+///   `THREAD_STATE_DEBUG_REGS`
+/// * `buffer` - User buffer for the state
+/// * `buffer_size` - Size of `buffer`
+///
+/// # Returns
+///
+/// * On success: Number of bytes written
+/// * On error: Negative error code
+pub fn sys_thread_read_state_impl(thread_handle: u32, kind: u32, buffer: usize, buffer_size: usize) -> SyscallRet {
+    log_debug!("sys_thread_read_state: handle={:#x} kind={}", thread_handle, kind);
+
+    let Some(thread) = lookup_thread(thread_handle as ThreadId) else {
+        log_error!("sys_thread_read_state: thread not found");
+        return err_to_ret(RX_ERR_BAD_HANDLE);
+    };
+    if kind == THREAD_STATE_DEBUG_REGS {
+        if buffer_size < core::mem::size_of::<DebugRegs>() {
+            return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+        }
+        let regs = exception::debug_regs(&thread);
+        let len = core::mem::size_of::<DebugRegs>();
+        if let Err(err) = unsafe { copy_to_user(UserPtr::<u8>::new(buffer), &regs as *const DebugRegs as *const u8, len) } {
+            log_error!("sys_thread_read_state: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+        return ok_to_ret(len);
+    }
+    let Some(pending) = exception::pending(thread_handle as ThreadId) else {
+        return err_to_ret(RX_ERR_BAD_STATE);
+    };
+
+    let state: &[u8] = match kind {
+        THREAD_STATE_GENERAL_REGS => {
+            if buffer_size < core::mem::size_of::<GeneralRegs>() {
+                return err_to_ret(RX_ERR_BUFFER_TOO_SMALL);
+            }
+            unsafe {
+                core::slice::from_raw_parts(
+                    &pending.regs as *const GeneralRegs as *const u8,
+                    core::mem::size_of::<GeneralRegs>(),
+                )
+            }
+        }
+        THREAD_STATE_STACK => &pending.stack[..pending.stack.len().min(buffer_size)],
+        _ => return err_to_ret(RX_ERR_INVALID_ARGS),
+    };
+
+    unsafe {
+        if let Err(err) = copy_to_user(UserPtr::<u8>::new(buffer), state.as_ptr(), state.len()) {
+            log_error!("sys_thread_read_state: copy_to_user failed: {:?}", err);
+            return err_to_ret(err.into());
+        }
+    }
+
+    ok_to_ret(state.len())
+}
+
+/// Write thread state syscall handler
+///
+/// Only `THREAD_STATE_DEBUG_REGS` can be written, and only with
+/// `kernel.enable-debugging-syscalls=true`. The breakpoints take effect
+/// the next time the thread is switched in.
+///
+/// # Arguments
+///
+/// * `thread_handle` - Handle to the thread
+/// * `kind` - `THREAD_STATE_DEBUG_REGS`
+/// * `buffer` - User buffer holding the state
+/// * `buffer_size` - Size of `buffer`
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_thread_write_state_impl(thread_handle: u32, kind: u32, buffer: usize, buffer_size: usize) -> SyscallRet {
+    log_debug!("sys_thread_write_state: handle={:#x} kind={}", thread_handle, kind);
+
+    if !cmdline::cmdline_get_bool(CMDLINE_DEBUGGING_SYSCALLS, false) {
+        return err_to_ret(RX_ERR_NOT_SUPPORTED);
+    }
+    let Some(thread) = lookup_thread(thread_handle as ThreadId) else {
+        log_error!("sys_thread_write_state: thread not found");
+        return err_to_ret(RX_ERR_BAD_HANDLE);
+    };
+    if kind != THREAD_STATE_DEBUG_REGS || buffer_size != core::mem::size_of::<DebugRegs>() {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    let mut regs = DebugRegs::default();
+    let len = core::mem::size_of::<DebugRegs>();
+    if let Err(err) = unsafe { copy_from_user(&mut regs as *mut DebugRegs as *mut u8, UserPtr::<u8>::new(buffer), len) } {
+        log_error!("sys_thread_write_state: copy_from_user failed: {:?}", err);
+        return err_to_ret(err.into());
+    }
+
+    match exception::set_debug_regs(&thread, &regs) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// Resume thread syscall handler
+///
+/// Releases a thread waiting for its exception handler, which then dies
+/// with its process.
+///
+/// # Arguments
+///
+/// * `thread_handle` - Handle to the thread
+/// * `options` - Must be 0
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_thread_resume_impl(thread_handle: u32, options: u32) -> SyscallRet {
+    log_debug!("sys_thread_resume: handle={:#x}", thread_handle);
+
+    if options != 0 {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+    if lookup_thread(thread_handle as ThreadId).is_none() {
+        log_error!("sys_thread_resume: thread not found");
+        return err_to_ret(RX_ERR_BAD_HANDLE);
+    }
+
+    match exception::resume(thread_handle as ThreadId) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: Thread Core Dump
+/// ============================================================================
+
+/// Write core dump syscall handler
+///
+/// Dumps the process of a thread waiting for its exception handler into
+/// a new VMO. See `object::coredump` for the format.
+///
+/// # Arguments
+///
+/// * `thread_handle` - Handle to the thread
+/// * `options` - 0 or `COREDUMP_ALL_MEMORY`
+///
+/// # Returns
+///
+/// * On success: Handle to the VMO
+/// * On error: Negative error code
+pub fn sys_thread_write_coredump_impl(thread_handle: u32, options: u32) -> SyscallRet {
+    log_debug!("sys_thread_write_coredump: handle={:#x} options={:#x}", thread_handle, options);
+
+    if lookup_thread(thread_handle as ThreadId).is_none() {
+        log_error!("sys_thread_write_coredump: thread not found");
+        return err_to_ret(RX_ERR_BAD_HANDLE);
+    }
+    let Some(pending) = exception::pending(thread_handle as ThreadId) else {
+        return err_to_ret(RX_ERR_BAD_STATE);
+    };
+
+    let dump = match coredump::write(&pending, options) {
+        Ok(dump) => dump,
+        Err(err) => {
+            log_error!("sys_thread_write_coredump: dump failed: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+    match vmo::register_vmo(dump) {
+        Ok(handle) => ok_to_ret(handle as usize),
+        Err(err) => err_to_ret(err),
+    }
+}
+
 /// ============================================================================
 /// Syscall: Process Create
 /// ============================================================================
@@ -395,6 +670,14 @@ pub fn sys_process_create_impl(
     let leaked_name: &'static str = Box::leak(process_name.into_boxed_str());
     process.set_name(leaked_name);
 
+    // The new process reads the same UTC clock as its creator
+    let creator = thread::get_current_thread()
+        .and_then(|thread| thread.pid())
+        .and_then(process::lookup);
+    if let Some(creator) = creator {
+        process.swap_utc_clock(creator.utc_clock());
+    }
+
     let pid = process.pid();
     log_debug!("sys_process_create: created process pid={}", pid);
 
@@ -446,7 +729,7 @@ pub fn sys_process_start_impl(
         Some(p) => p,
         None => {
             log_error!("sys_process_start: process not found");
-            return err_to_ret(RX_ERR_INVALID_ARGS);
+            return err_to_ret(RX_ERR_BAD_HANDLE);
         }
     };
 
@@ -460,7 +743,7 @@ pub fn sys_process_start_impl(
         Some(t) => t,
         None => {
             log_error!("sys_process_start: thread not found");
-            return err_to_ret(RX_ERR_INVALID_ARGS);
+            return err_to_ret(RX_ERR_BAD_HANDLE);
         }
     };
 
@@ -476,6 +759,151 @@ pub fn sys_process_start_impl(
     }
 }
 
+/// ============================================================================
+/// Syscall: Process Memory
+/// ============================================================================
+
+/// Command line option that enables the process memory syscalls and
+/// writing debug registers
+const CMDLINE_DEBUGGING_SYSCALLS: &str = "kernel.enable-debugging-syscalls";
+
+/// Most bytes one process memory syscall transfers
+const MAX_MEMORY_TRANSFER: usize = 64 * 1024 * 1024;
+
+/// Process memory is copied through the kernel a page at a time
+const MEMORY_CHUNK: usize = 4096;
+
+/// Look up the process a debugger wants to read or write
+///
+/// Reading and writing other processes is a debugging tool: it is off
+/// unless `kernel.enable-debugging-syscalls=true`.
+fn debug_target(process_handle: u32) -> Result<&'static Process> {
+    if !cmdline::cmdline_get_bool(CMDLINE_DEBUGGING_SYSCALLS, false) {
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+    let process = process::lookup(process_handle as process::ProcessId).ok_or(RX_ERR_BAD_HANDLE)?;
+    if !process.state().is_alive() {
+        return Err(RX_ERR_BAD_STATE);
+    }
+    Ok(process)
+}
+
+/// Read process memory syscall handler
+///
+/// Pages the process hasn't touched yet are committed, as a fault would.
+///
+/// # Arguments
+///
+/// * `process_handle` - Handle to the process
+/// * `vaddr` - Address in the process
+/// * `buffer` - User buffer for the memory
+/// * `buffer_size` - Bytes to read
+///
+/// # Returns
+///
+/// * On success: Number of bytes read; fewer than `buffer_size` if the
+///   memory stops being mapped
+/// * On error: Negative error code
+pub fn sys_process_read_memory_impl(process_handle: u32, vaddr: u64, buffer: usize, buffer_size: usize) -> SyscallRet {
+    log_debug!(
+        "sys_process_read_memory: process={:#x} vaddr={:#x} size={:#x}",
+        process_handle, vaddr, buffer_size
+    );
+
+    if buffer_size == 0 || buffer_size > MAX_MEMORY_TRANSFER {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+    let vmar = match debug_target(process_handle).and_then(|_| vmar::user_vmar()) {
+        Ok(vmar) => vmar,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let mut chunk = alloc::vec![0u8; MEMORY_CHUNK];
+    let mut done = 0;
+    while done < buffer_size {
+        let len = (buffer_size - done).min(MEMORY_CHUNK);
+        let read = match vmar.read_memory(vaddr + done as u64, &mut chunk[..len]) {
+            Ok(read) => read,
+            Err(_) if done > 0 => break,
+            Err(err) => return err_to_ret(err),
+        };
+        unsafe {
+            if let Err(err) = copy_to_user(UserPtr::<u8>::new(buffer + done), chunk.as_ptr(), read) {
+                log_error!("sys_process_read_memory: copy_to_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+        done += read;
+        if read < len {
+            break;
+        }
+    }
+
+    if done == 0 {
+        return err_to_ret(RX_ERR_NO_MEMORY);
+    }
+    ok_to_ret(done)
+}
+
+/// Write process memory syscall handler
+///
+/// Read-only and executable mappings are written too, so a debugger can
+/// patch in breakpoints; the instruction cache is kept in sync.
+///
+/// # Arguments
+///
+/// * `process_handle` - Handle to the process
+/// * `vaddr` - Address in the process
+/// * `buffer` - User buffer with the data
+/// * `buffer_size` - Bytes to write
+///
+/// # Returns
+///
+/// * On success: Number of bytes written; fewer than `buffer_size` if the
+///   memory stops being mapped
+/// * On error: Negative error code
+pub fn sys_process_write_memory_impl(process_handle: u32, vaddr: u64, buffer: usize, buffer_size: usize) -> SyscallRet {
+    log_debug!(
+        "sys_process_write_memory: process={:#x} vaddr={:#x} size={:#x}",
+        process_handle, vaddr, buffer_size
+    );
+
+    if buffer_size == 0 || buffer_size > MAX_MEMORY_TRANSFER {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+    let vmar = match debug_target(process_handle).and_then(|_| vmar::user_vmar()) {
+        Ok(vmar) => vmar,
+        Err(err) => return err_to_ret(err),
+    };
+
+    let mut chunk = alloc::vec![0u8; MEMORY_CHUNK];
+    let mut done = 0;
+    while done < buffer_size {
+        let len = (buffer_size - done).min(MEMORY_CHUNK);
+        unsafe {
+            if let Err(err) = copy_from_user(chunk.as_mut_ptr(), UserPtr::<u8>::new(buffer + done), len) {
+                log_error!("sys_process_write_memory: copy_from_user failed: {:?}", err);
+                return err_to_ret(err.into());
+            }
+        }
+        let written = match vmar.write_memory(vaddr + done as u64, &chunk[..len]) {
+            Ok(written) => written,
+            Err(_) if done > 0 => break,
+            Err(err) => return err_to_ret(err),
+        };
+        done += written;
+        if written < len {
+            break;
+        }
+    }
+
+    if done == 0 {
+        return err_to_ret(RX_ERR_NO_MEMORY);
+    }
+    log_info!("sys_process_write_memory: wrote {} bytes at {:#x} in pid {}", done, vaddr, process_handle);
+    ok_to_ret(done)
+}
+
 /// ============================================================================
 /// Syscall: Process Exit
 /// ============================================================================
@@ -498,11 +926,15 @@ pub fn sys_process_exit_impl(exit_code: i64) -> SyscallRet {
 
     log_info!("Process exiting with code {}", exit_code);
 
-    // In a real implementation, this would:
+    // Close everything the process still holds; waits on it are canceled
+    let handle_table = thread::current_thread_handle_table();
+    let closed = handle_ops::close_all_handles(handle_table);
+    log_debug!("sys_process_exit: closed {} handles", closed);
+
+    // In a real implementation, this would also:
     // 1. Terminate all threads in the process
-    // 2. Clean up resources
-    // 3. Notify parent process
-    // 4. Call scheduler to switch to another thread
+    // 2. Notify parent process
+    // 3. Call scheduler to switch to another thread
 
     ok_to_ret(0)
 }
@@ -550,7 +982,7 @@ pub fn sys_task_kill_impl(task_handle: u32) -> SyscallRet {
     }
 
     log_error!("sys_task_kill: task not found");
-    err_to_ret(RX_ERR_INVALID_ARGS)
+    err_to_ret(RX_ERR_BAD_HANDLE)
 }
 
 /// ============================================================================
@@ -585,7 +1017,7 @@ pub fn sys_job_create_impl(parent_job: u32, options: u32) -> SyscallRet {
         Some(job) => job,
         None => {
             log_error!("sys_job_create: parent job not found");
-            return err_to_ret(RX_ERR_INVALID_ARGS);
+            return err_to_ret(RX_ERR_BAD_HANDLE);
         }
     };
 
@@ -609,6 +1041,47 @@ pub fn sys_job_create_impl(parent_job: u32, options: u32) -> SyscallRet {
     ok_to_ret(child_job.id as usize)
 }
 
+/// ============================================================================
+/// Syscall: Task Exception Channel
+/// ============================================================================
+
+/// Create an exception channel syscall handler
+///
+/// # Arguments
+///
+/// * `task_handle` - Job handle (0 for root job)
+/// * `options` - Must be 0
+///
+/// # Returns
+///
+/// * On success: Handle to the handler's end of the channel
+/// * On error: Negative error code
+pub fn sys_task_create_exception_channel_impl(task_handle: u32, options: u32) -> SyscallRet {
+    log_debug!("sys_task_create_exception_channel: handle={:#x}", task_handle);
+
+    if options != 0 {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    let job_id = if task_handle == 0 {
+        job::JOB_ID_ROOT
+    } else {
+        task_handle as JobId
+    };
+    if job::lookup(job_id).is_none() {
+        log_error!("sys_task_create_exception_channel: job not found");
+        return err_to_ret(RX_ERR_BAD_HANDLE);
+    }
+
+    match exception::bind(job_id) {
+        Ok(channel) => ok_to_ret(channel as usize),
+        Err(err) => {
+            log_error!("sys_task_create_exception_channel: failed to bind: {:?}", err);
+            err_to_ret(err)
+        }
+    }
+}
+
 /// ============================================================================
 /// Module Statistics
 /// ============================================================================
@@ -661,7 +1134,7 @@ mod tests {
     #[test]
     fn test_thread_registry() {
         let thread = Thread::new(0, String::from("test")).unwrap();
-        let thread_arc = Arc::new(thread);
+        let thread_arc = thread.into_ref();
 
         let tid = unsafe { THREAD_REGISTRY.insert(thread_arc.clone()).unwrap() };
         assert_eq!(tid, thread_arc.tid());
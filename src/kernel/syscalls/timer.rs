@@ -149,13 +149,10 @@ static TIMER_REGISTRY: Mutex<TimerRegistry> = Mutex::new(TimerRegistry::new());
 
 /// Get the current process's handle table
 ///
-/// This is a placeholder that returns NULL for now.
-/// In a real implementation, this would use thread-local storage
-/// or per-CPU data to get the current process.
+/// Uses the same table as the handle syscalls until processes are
+/// tracked per thread.
 fn current_process_handle_table() -> Option<&'static HandleTable> {
-    // TODO: Implement proper current process tracking
-    // For now, return None to indicate not implemented
-    None
+    Some(crate::kernel::thread::current_thread_handle_table())
 }
 
 /// Look up a timer from a handle value
@@ -175,7 +172,7 @@ fn lookup_timer_from_handle(
 
     // Get the handle from the table
     let handle = handle_table.get(handle_val)
-        .ok_or(RX_ERR_INVALID_ARGS)?;
+        .ok_or(RX_ERR_BAD_HANDLE)?;
 
     // Validate object type
     if handle.obj_type() != ObjectType::Timer {
@@ -201,7 +198,7 @@ fn lookup_timer_from_handle(
 
 /// Create a kernel object base for a timer
 fn timer_to_kernel_base(timer: &Arc<Timer>) -> KernelObjectBase {
-    KernelObjectBase::new(ObjectType::Timer)
+    KernelObjectBase::with_koid(ObjectType::Timer, timer.koid)
 }
 
 /// ============================================================================
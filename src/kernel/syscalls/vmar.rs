@@ -21,16 +21,24 @@
 //!
 //! - Hierarchical address regions (parent-child relationships)
 //! - Region tree for efficient allocation and overlap detection
-//! - VMO mapping with permissions and cache policy
+//! - VMO mapping with permissions and cache policy; a mapping takes the
+//!   VMO's policy unless `MAP_UNCACHED` or `MAP_WRITE_COMBINING` overrides
+//!   it for a physical VMO
 //! - Protection flags (READ/WRITE/EXECUTE)
-//! - Address space management with proper alignment
+//! - W^X: a mapping may not be writable and executable at the same time
+//!   unless its VMAR was allocated with `CAN_MAP_WRITE_EXECUTE`
+//! - Placement options: exact (`SPECIFIC`, `SPECIFIC_OVERWRITE`), bounded
+//!   (`OFFSET_IS_UPPER_LIMIT`) or anywhere, aligned up to 4 GiB (`ALIGN_*`)
+//!   so mappings can be backed by large pages
 
 
+use crate::kernel::lib::ktrace;
 use crate::kernel::object::vmo::{self, Vmo, VmoId};
 use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
-use crate::kernel::sync::Mutex;
+use crate::kernel::sync::{Mutex, RwLock};
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::kernel::usercopy::{copy_to_user, UserPtr};
+use crate::kernel::vm::aslr;
 use crate::kernel::vm::aspace::*;
 use crate::kernel::vm::layout::*;
 use crate::kernel::vm::page_table::*;
@@ -53,7 +61,10 @@ use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 /// VMAR options
 pub mod vmar_options {
+    use crate::kernel::vm::layout::PAGE_SIZE;
     use crate::kernel::vm::MemProt;
+    use crate::rustux::types::err::RX_ERR_INVALID_ARGS;
+    use crate::rustux::types::Result;
 
     /// Permission flags
     pub const PERM_READ: u32 = 0x01;
@@ -72,12 +83,97 @@ pub mod vmar_options {
     pub const REQUIRE_NON_RESIZABLE: u32 = 0x800;
     pub const ALLOW_NON_RESIZABLE: u32 = 0x1000;
 
+    /// Opt out of W^X for mappings in this VMAR (JITs and similar).
+    /// Only honored together with CAN_MAP_WRITE and CAN_MAP_EXECUTE.
+    pub const CAN_MAP_WRITE_EXECUTE: u32 = 0x2000;
+
+    /// Map uncached or write-combining instead of with the VMO's cache
+    /// policy. Only physical VMOs may be mapped this way.
+    pub const MAP_UNCACHED: u32 = 0x4000;
+    pub const MAP_WRITE_COMBINING: u32 = 0x8000;
+
+    /// Place the mapping anywhere that ends at or below the given offset,
+    /// instead of exactly at it. Cannot be combined with SPECIFIC.
+    pub const OFFSET_IS_UPPER_LIMIT: u32 = 0x1_0000;
+
+    /// Place the mapping in the lowest free range rather than starting the
+    /// search at the VMAR's randomized hint
+    pub const COMPACT: u32 = 0x2_0000;
+
+    /// Alignment of the placement, as log2 of the byte alignment in bits
+    /// 24-28. Zero means page alignment; otherwise 12..=32.
+    pub const ALIGN_SHIFT: u32 = 24;
+    pub const ALIGN_MASK: u32 = 0x1F << ALIGN_SHIFT;
+    pub const ALIGN_4KB: u32 = 12 << ALIGN_SHIFT;
+    pub const ALIGN_64KB: u32 = 16 << ALIGN_SHIFT;
+    pub const ALIGN_2MB: u32 = 21 << ALIGN_SHIFT;
+    pub const ALIGN_1GB: u32 = 30 << ALIGN_SHIFT;
+    pub const ALIGN_4GB: u32 = 32 << ALIGN_SHIFT;
+
+    /// All cache policy flags
+    pub const CACHE_FLAGS: u32 = MAP_UNCACHED | MAP_WRITE_COMBINING;
+
     /// All permission flags
     pub const PERM_FLAGS: u32 = PERM_READ | PERM_WRITE | PERM_EXECUTE;
 
     /// All capability flags
     pub const CAN_MAP_FLAGS: u32 = CAN_MAP_READ | CAN_MAP_WRITE | CAN_MAP_EXECUTE;
 
+    /// Both ways of asking for an exact address
+    pub const SPECIFIC_FLAGS: u32 = SPECIFIC | SPECIFIC_OVERWRITE;
+
+    /// Every option `rx_vmar_map` accepts
+    pub const MAP_FLAGS: u32 = PERM_FLAGS
+        | SPECIFIC_FLAGS
+        | MAP_RANGE
+        | REQUIRE_NON_RESIZABLE
+        | ALLOW_NON_RESIZABLE
+        | CACHE_FLAGS
+        | OFFSET_IS_UPPER_LIMIT
+        | COMPACT
+        | ALIGN_MASK;
+
+    /// Byte alignment selected by the ALIGN_* bits of `options`
+    ///
+    /// `None` if the encoded log2 is neither zero nor in 12..=32.
+    pub fn alignment(options: u32) -> Option<u64> {
+        match (options & ALIGN_MASK) >> ALIGN_SHIFT {
+            0 => Some(PAGE_SIZE as u64),
+            log2 @ 12..=32 => Some(1 << log2),
+            _ => None,
+        }
+    }
+
+    /// Check a set of `rx_vmar_map` options against the requested offsets
+    ///
+    /// Returns the placement alignment. Fails with `RX_ERR_INVALID_ARGS` on
+    /// unknown bits, a bad alignment, SPECIFIC combined with
+    /// OFFSET_IS_UPPER_LIMIT or COMPACT, a nonzero `vmar_offset` that is
+    /// neither an address nor a limit, a specific address that isn't
+    /// aligned, or an unaligned `vmo_offset`.
+    pub fn check_map(options: u32, vmar_offset: u64, vmo_offset: u64) -> Result<u64> {
+        if options & !MAP_FLAGS != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        let alignment = alignment(options).ok_or(RX_ERR_INVALID_ARGS)?;
+
+        if options & SPECIFIC_FLAGS != 0 {
+            if options & (OFFSET_IS_UPPER_LIMIT | COMPACT) != 0 {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+            if vmar_offset & (alignment - 1) != 0 {
+                return Err(RX_ERR_INVALID_ARGS);
+            }
+        } else if options & OFFSET_IS_UPPER_LIMIT == 0 && vmar_offset != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+
+        if vmo_offset & (PAGE_SIZE as u64 - 1) != 0 {
+            return Err(RX_ERR_INVALID_ARGS);
+        }
+        Ok(alignment)
+    }
+
     /// Convert permission flags to memory protection
     pub fn perm_to_prot(perm: u32) -> MemProt {
         let mut prot = MemProt::None;
@@ -167,6 +263,25 @@ impl VmarRegion {
     }
 }
 
+/// One VMO mapping, as listed by [`Vmar::mappings`]
+#[derive(Clone)]
+pub struct VmarMapping {
+    /// Address of the mapping
+    pub addr: u64,
+
+    /// Size of the mapping
+    pub size: u64,
+
+    /// VMO being mapped
+    pub vmo: Arc<Vmo>,
+
+    /// Offset within the VMO
+    pub vmo_offset: u64,
+
+    /// Memory protection
+    pub prot: MemProt,
+}
+
 /// ============================================================================
 /// VMAR Structure
 /// ============================================================================
@@ -189,7 +304,9 @@ pub struct Vmar {
     parent: Option<*const Vmar>,
 
     /// Child regions keyed by base address
-    children: Mutex<BTreeMap<u64, VmarRegion>>,
+    ///
+    /// Looked up on every fault and rarely changed, so readers share it.
+    children: RwLock<BTreeMap<u64, VmarRegion>>,
 
     /// VMAR state flags
     flags: VmarFlags,
@@ -197,6 +314,9 @@ pub struct Vmar {
     /// Alignment mask for allocations (0 = no alignment requirement)
     align_mask: u64,
 
+    /// Offset non-SPECIFIC allocations start searching from (randomized)
+    alloc_hint: u64,
+
     /// Reference count
     ref_count: AtomicU64,
 }
@@ -213,6 +333,9 @@ struct VmarFlags {
     /// Can map with EXECUTE permission
     can_map_execute: bool,
 
+    /// Can map with WRITE and EXECUTE together (W^X override)
+    can_map_write_execute: bool,
+
     /// Is this a root VMAR?
     is_root: bool,
 
@@ -227,6 +350,7 @@ impl VmarFlags {
             can_map_read: false,
             can_map_write: false,
             can_map_execute: false,
+            can_map_write_execute: false,
             is_root: false,
             destroyed: false,
         }
@@ -238,6 +362,9 @@ impl VmarFlags {
             can_map_read: (options & vmar_options::CAN_MAP_READ) != 0,
             can_map_write: (options & vmar_options::CAN_MAP_WRITE) != 0,
             can_map_execute: (options & vmar_options::CAN_MAP_EXECUTE) != 0,
+            can_map_write_execute: (options & vmar_options::CAN_MAP_WRITE_EXECUTE) != 0
+                && (options & vmar_options::CAN_MAP_WRITE) != 0
+                && (options & vmar_options::CAN_MAP_EXECUTE) != 0,
             is_root,
             destroyed: false,
         }
@@ -254,8 +381,17 @@ impl VmarFlags {
         if (perm & vmar_options::PERM_EXECUTE) != 0 && !self.can_map_execute {
             return false;
         }
+        if !Self::is_wxorx(perm) && !self.can_map_write_execute {
+            return false;
+        }
         true
     }
+
+    /// Check that a permission set isn't both writable and executable
+    const fn is_wxorx(perm: u32) -> bool {
+        let wx = vmar_options::PERM_WRITE | vmar_options::PERM_EXECUTE;
+        perm & wx != wx
+    }
 }
 
 unsafe impl Send for Vmar {}
@@ -265,21 +401,27 @@ impl Vmar {
     /// Create a new root VMAR
     pub fn new_root(base: u64, size: u64) -> Arc<Self> {
         let id = Self::alloc_id();
+        let layout = aslr::UserLayout::new(
+            base as crate::rustux::types::VAddr,
+            (base + size) as crate::rustux::types::VAddr,
+        );
 
         Arc::new(Self {
             id,
             base,
             size,
             parent: None,
-            children: Mutex::new(BTreeMap::new()),
+            children: RwLock::new(BTreeMap::new()),
             flags: VmarFlags {
                 can_map_read: true,
                 can_map_write: true,
                 can_map_execute: true,
+                can_map_write_execute: false,
                 is_root: true,
                 destroyed: false,
             },
             align_mask: 0,
+            alloc_hint: layout.mmap_base as u64 - base,
             ref_count: AtomicU64::new(1),
         })
     }
@@ -313,14 +455,15 @@ impl Vmar {
             base: offset,
             size,
             parent: Some(Arc::as_ptr(parent) as *const Vmar),
-            children: Mutex::new(BTreeMap::new()),
+            children: RwLock::new(BTreeMap::new()),
             flags: VmarFlags::from_options(options, false),
             align_mask,
+            alloc_hint: aslr::random_offset(size as usize / 8, PAGE_SIZE) as u64,
             ref_count: AtomicU64::new(1),
         });
 
         // Add to parent's children
-        let mut parent_children = parent.children.lock();
+        let mut parent_children = parent.children.write();
         if parent_children.contains_key(&offset) {
             return Err(RX_ERR_ALREADY_EXISTS);
         }
@@ -340,6 +483,11 @@ impl Vmar {
         self.id
     }
 
+    /// Check if a mapping with the given permission flags is allowed here
+    pub fn can_map(&self, perm: u32) -> bool {
+        self.flags.can_map(perm)
+    }
+
     /// Check if this VMAR has been destroyed
     pub fn is_destroyed(&self) -> bool {
         self.flags.destroyed
@@ -359,27 +507,51 @@ impl Vmar {
     }
 
     /// Find a free region in this VMAR
+    ///
+    /// The search starts at the allocation hint and wraps around to the
+    /// bottom of the VMAR if nothing above it fits.
     fn find_free_region(&self, size: u64, alignment: u64) -> Option<u64> {
-        let children = self.children.lock();
-        let mut prev_end = 0u64;
+        self.find_free_region_below(self.alloc_hint, self.size, size, alignment)
+    }
+
+    /// Find a free region that ends at or below `limit`
+    ///
+    /// Like `find_free_region`, but starting the search at `start`.
+    fn find_free_region_below(&self, start: u64, limit: u64, size: u64, alignment: u64) -> Option<u64> {
+        let children = self.children.read();
+        let limit = limit.min(self.size);
+        Self::find_free_region_from(&children, start, limit, size, alignment)
+            .or_else(|| Self::find_free_region_from(&children, 0, limit, size, alignment))
+    }
+
+    /// Find the lowest free, aligned range at or above `start`
+    fn find_free_region_from(
+        children: &BTreeMap<u64, VmarRegion>,
+        start: u64,
+        limit: u64,
+        size: u64,
+        alignment: u64,
+    ) -> Option<u64> {
+        let align_up = |addr: u64| (addr + alignment - 1) & !(alignment - 1);
+        let mut candidate = align_up(start);
 
         for (&base, region) in children.iter() {
-            let region_size = region.size();
-            let region_end = base + region_size;
+            let region_end = base + region.size();
+            if region_end <= candidate {
+                continue;
+            }
 
-            // Check if there's space between prev_end and this region
-            let aligned_start = (prev_end + alignment - 1) & !(alignment - 1);
-            if aligned_start + size <= base {
-                return Some(aligned_start);
+            // Check if there's space between the candidate and this region
+            if candidate + size <= base {
+                break;
             }
 
-            prev_end = region_end;
+            candidate = align_up(region_end);
         }
 
-        // Check space after last region
-        let aligned_start = (prev_end + alignment - 1) & !(alignment - 1);
-        if aligned_start + size <= self.size {
-            Some(aligned_start)
+        // Check the space left below the limit
+        if candidate.checked_add(size)? <= limit {
+            Some(candidate)
         } else {
             None
         }
@@ -387,7 +559,7 @@ impl Vmar {
 
     /// Check if a range overlaps with any existing regions
     fn check_overlap(&self, offset: u64, size: u64) -> Result {
-        let children = self.children.lock();
+        let children = self.children.read();
         let end = offset + size;
 
         for (&base, region) in children.iter() {
@@ -403,7 +575,125 @@ impl Vmar {
         Ok(())
     }
 
+    /// Remove the mappings overlapping a range, for SPECIFIC_OVERWRITE
+    ///
+    /// Child VMARs are never overwritten; the range must only hold
+    /// mappings.
+    fn overwrite(&self, offset: u64, size: u64) -> Result {
+        let end = offset + size;
+        let hits_vmar = self.children.read().iter().any(|(&base, region)| {
+            matches!(region, VmarRegion::Vmar { .. }) && base < end && base + region.size() > offset
+        });
+        if hits_vmar {
+            return Err(RX_ERR_ALREADY_EXISTS);
+        }
+        self.unmap(offset, size)
+    }
+
+    /// Find the mapping covering `offset`, descending into child VMARs
+    ///
+    /// Returns the mapped VMO, the VMO offset backing `offset` and the
+    /// mapping's protection. Used to resolve page faults, so it only reads
+    /// the region trees.
+    pub fn lookup(&self, offset: u64) -> Option<(Arc<Vmo>, u64, MemProt)> {
+        let children = self.children.read();
+        let (&base, region) = children.range(..=offset).next_back()?;
+        if offset >= base + region.size() {
+            return None;
+        }
+        match region {
+            VmarRegion::Vmar { vmar } => vmar.lookup(offset - base),
+            VmarRegion::Mapping { vmo, vmo_offset, prot, .. } => {
+                Some((vmo.clone(), vmo_offset + (offset - base), *prot))
+            }
+        }
+    }
+
+    /// Every mapping in this VMAR and its children, in address order
+    ///
+    /// Addresses are offsets in this VMAR, as [`Vmar::lookup`] takes them.
+    pub fn mappings(&self) -> Vec<VmarMapping> {
+        let mut mappings = Vec::new();
+        self.collect_mappings(0, &mut mappings);
+        mappings
+    }
+
+    fn collect_mappings(&self, base: u64, mappings: &mut Vec<VmarMapping>) {
+        for (&offset, region) in self.children.read().iter() {
+            match region {
+                VmarRegion::Vmar { vmar } => vmar.collect_mappings(base + offset, mappings),
+                VmarRegion::Mapping { vmo, vmo_offset, size, prot, .. } => {
+                    mappings.push(VmarMapping {
+                        addr: base + offset,
+                        size: *size,
+                        vmo: vmo.clone(),
+                        vmo_offset: *vmo_offset,
+                        prot: *prot,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Read mapped memory from `offset` into `buf`, as a debugger would
+    ///
+    /// Goes through the mapped VMOs rather than the address space, so
+    /// protections don't apply and pages not yet committed are committed.
+    /// Stops at the first unmapped byte and returns how many bytes were
+    /// read. Physical VMOs are refused with `RX_ERR_ACCESS_DENIED`.
+    pub fn read_memory(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        self.access_memory(offset, buf.len(), |vmo, vmo_offset, done, len| {
+            vmo.read(vmo_offset, &mut buf[done..done + len])
+        })
+    }
+
+    /// Write `data` to mapped memory at `offset`, as a debugger would
+    ///
+    /// As [`Vmar::read_memory`]; read-only and executable mappings are
+    /// written too, and the instruction cache is kept in sync.
+    pub fn write_memory(&self, offset: u64, data: &[u8]) -> Result<usize> {
+        self.access_memory(offset, data.len(), |vmo, vmo_offset, done, len| {
+            let written = vmo.write(vmo_offset, &data[done..done + len])?;
+            vmo.sync_icache(vmo_offset, written);
+            Ok(written)
+        })
+    }
+
+    /// Call `f(vmo, vmo_offset, done, len)` on each page-bounded piece of
+    /// `[offset, offset + len)` that is mapped, up to the first that isn't
+    fn access_memory(
+        &self,
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(&Vmo, usize, usize, usize) -> Result<usize>,
+    ) -> Result<usize> {
+        let mut done = 0;
+        while done < len {
+            let addr = offset + done as u64;
+            let Some((vmo, vmo_offset, _)) = self.lookup(addr) else {
+                break;
+            };
+            if vmo.flags.is_physical() {
+                return Err(RX_ERR_ACCESS_DENIED);
+            }
+            let chunk = (PAGE_SIZE - addr as usize % PAGE_SIZE).min(len - done);
+            match f(&vmo, vmo_offset as usize, done, chunk) {
+                Ok(n) if n == chunk => done += n,
+                Ok(n) => return Ok(done + n),
+                Err(_) if done > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(done)
+    }
+
     /// Map a VMO into this VMAR (without address space binding)
+    ///
+    /// `options` are `rx_vmar_map` options and choose the placement:
+    /// exactly at `vmar_offset` with SPECIFIC (replacing existing mappings
+    /// with SPECIFIC_OVERWRITE), below it with OFFSET_IS_UPPER_LIMIT, and
+    /// otherwise anywhere, from the bottom of the VMAR with COMPACT. The
+    /// ALIGN_* bits set the alignment of the placement.
     pub fn map(
         &self,
         vmo: Arc<Vmo>,
@@ -425,28 +715,44 @@ impl Vmar {
             return Err(RX_ERR_ACCESS_DENIED);
         }
 
+        let alignment = vmar_options::check_map(options, vmar_offset, vmo_offset)?;
+
         // Page-align size
         let aligned_size = (size + PAGE_SIZE as u64 - 1) & !(PAGE_SIZE as u64 - 1);
 
+        // Validate VMO offset
+        match vmo_offset.checked_add(size) {
+            Some(end) if end <= vmo.size() as u64 => {}
+            _ => return Err(RX_ERR_INVALID_ARGS),
+        }
+
         // Find or validate offset
-        let offset = if options & vmar_options::SPECIFIC != 0 {
+        let offset = if options & vmar_options::SPECIFIC_FLAGS != 0 {
             // User specified a specific address
+            match vmar_offset.checked_add(aligned_size) {
+                Some(end) if end <= self.size => {}
+                _ => return Err(RX_ERR_INVALID_ARGS),
+            }
+            if options & vmar_options::SPECIFIC_OVERWRITE != 0 {
+                self.overwrite(vmar_offset, aligned_size)?;
+            }
             vmar_offset
         } else {
-            // Find a free region
-            self.find_free_region(aligned_size, PAGE_SIZE as u64)
+            let limit = if options & vmar_options::OFFSET_IS_UPPER_LIMIT != 0 {
+                vmar_offset
+            } else {
+                self.size
+            };
+            let start = if options & vmar_options::COMPACT != 0 { 0 } else { self.alloc_hint };
+            self.find_free_region_below(start, limit, aligned_size, alignment)
                 .ok_or(RX_ERR_NO_RESOURCES)?
         };
 
         // Check for overlap
         self.check_overlap(offset, aligned_size)?;
 
-        // Validate VMO offset
-        if vmo_offset + (size as u64) > vmo.size() as u64 {
-            return Err(RX_ERR_INVALID_ARGS);
-        }
-
         // Create mapping region
+        vmo.mapping_added();
         let mapping = VmarRegion::Mapping {
             vmo,
             vmo_offset,
@@ -456,7 +762,7 @@ impl Vmar {
         };
 
         // Insert into children
-        let mut children = self.children.lock();
+        let mut children = self.children.write();
         children.insert(offset, mapping);
 
         Ok(offset)
@@ -466,19 +772,21 @@ impl Vmar {
     ///
     /// This method creates a mapping and performs the actual page table
     /// manipulation to map the VMO's pages into the specified address space.
+    /// The mapping is recorded on the VMO so resizes and decommits can
+    /// remove pages from it.
     pub fn map_to_aspace(
         &self,
-        aspace: &crate::kernel::vm::aspace::AddressSpace,
+        aspace: &Arc<crate::kernel::vm::aspace::AddressSpace>,
         vmo: Arc<Vmo>,
         vmar_offset: u64,
         vmo_offset: u64,
         size: u64,
         prot: MemProt,
-        _cache_policy: vmo::CachePolicy,
+        cache_policy: vmo::CachePolicy,
         options: u32,
     ) -> Result<u64> {
         // Create the mapping in VMAR
-        let offset = self.map(vmo.clone(), vmar_offset, vmo_offset, size, prot, vmo::CachePolicy::Default, options)?;
+        let offset = self.map(vmo.clone(), vmar_offset, vmo_offset, size, prot, cache_policy, options)?;
 
         // Calculate the virtual address to map at
         // The VMAR offset is relative to the VMAR's base
@@ -505,7 +813,7 @@ impl Vmar {
 
             // Map into address space
             // Convert virtual address back to physical for mapping
-            if let Err(err) = aspace.map(current_vaddr, paddr as usize, 1, prot) {
+            if let Err(err) = aspace.map_with_cache_policy(current_vaddr, paddr as usize, 1, prot, cache_policy) {
                 log_error!("VMAR: Failed to map page at {:#x}: {:?}", current_vaddr, err);
                 // Clean up previously mapped pages
                 for j in 0..i {
@@ -519,6 +827,8 @@ impl Vmar {
         // Flush TLB for the mapped region
         aspace.flush_tlb();
 
+        vmo.add_aspace_mapping(aspace.clone(), vaddr, vmo_offset as usize, page_count * PAGE_SIZE);
+
         log_debug!(
             "VMAR: Mapped VMO at vaddr={:#x} pages={} prot={:?}",
             vaddr,
@@ -536,7 +846,7 @@ impl Vmar {
             return Err(RX_ERR_BAD_STATE);
         }
 
-        let mut children = self.children.lock();
+        let mut children = self.children.write();
 
         // Find overlapping mappings
         let start = offset;
@@ -552,9 +862,13 @@ impl Vmar {
             .map(|(&base, _)| base)
             .collect();
 
-        // Remove mappings
-        for key in keys_to_remove {
-            children.remove(&key);
+        // Remove mappings, dropping the VMO references after the lock
+        let removed: Vec<VmarRegion> = keys_to_remove.iter().filter_map(|key| children.remove(key)).collect();
+        drop(children);
+        for region in removed {
+            if let VmarRegion::Mapping { vmo, .. } = region {
+                vmo.mapping_removed();
+            }
         }
 
         Ok(())
@@ -570,6 +884,15 @@ impl Vmar {
         // Calculate the virtual address
         let vaddr = (self.base + offset) as usize;
 
+        // The VMO no longer needs to fix up this mapping
+        let vmo = match self.children.read().get(&offset) {
+            Some(VmarRegion::Mapping { vmo, .. }) => Some(vmo.clone()),
+            _ => None,
+        };
+        if let Some(vmo) = vmo {
+            vmo.remove_aspace_mapping(aspace, vaddr);
+        }
+
         // Unmap from address space first
         let page_count = (size / PAGE_SIZE as u64) as usize;
         if page_count > 0 {
@@ -590,6 +913,51 @@ impl Vmar {
         Ok(())
     }
 
+    /// Harvest the accessed/dirty bits of the mappings in
+    /// `[offset, offset + size)`
+    ///
+    /// Ages the mapped VMO pages as [`Vmo::harvest`] does, descending into
+    /// child VMARs. Returns the (offset within this VMAR, age) of each
+    /// committed page, in address order.
+    pub fn harvest(&self, offset: u64, size: u64) -> Result<Vec<(u64, crate::kernel::pmm::PageAge)>> {
+        if self.flags.destroyed {
+            return Err(RX_ERR_BAD_STATE);
+        }
+        let end = offset.checked_add(size).ok_or(RX_ERR_OUT_OF_RANGE)?;
+
+        // Harvesting takes other locks, so don't hold ours meanwhile
+        let regions: Vec<(u64, u64, Option<Arc<Vmar>>, Option<(Arc<Vmo>, u64)>)> = self
+            .children
+            .read()
+            .range(..end)
+            .filter(|(&base, region)| base + region.size() > offset)
+            .map(|(&base, region)| match region {
+                VmarRegion::Vmar { vmar } => (base, vmar.size, Some(vmar.clone()), None),
+                VmarRegion::Mapping { vmo, vmo_offset, size, .. } => {
+                    (base, *size, None, Some((vmo.clone(), *vmo_offset)))
+                }
+            })
+            .collect();
+
+        let mut ages = Vec::new();
+        for (base, region_size, child, mapping) in regions {
+            let lo = offset.max(base);
+            let hi = end.min(base + region_size);
+            if let Some(vmar) = child {
+                for (child_offset, age) in vmar.harvest(lo - base, hi - lo)? {
+                    ages.push((base + child_offset, age));
+                }
+            } else if let Some((vmo, vmo_offset)) = mapping {
+                let start = vmo_offset + (lo - base);
+                for (page_offset, age) in vmo.harvest(start as usize, (hi - lo) as usize)? {
+                    ages.push((base + (page_offset as u64 - vmo_offset), age));
+                }
+            }
+        }
+
+        Ok(ages)
+    }
+
     /// Change protection for a region
     pub fn protect(&self, offset: u64, size: u64, new_prot: MemProt) -> Result {
         // Check if VMAR is destroyed
@@ -603,7 +971,7 @@ impl Vmar {
             return Err(RX_ERR_ACCESS_DENIED);
         }
 
-        let mut children = self.children.lock();
+        let mut children = self.children.write();
 
         // Find the mapping at this offset
         if let Some(region) = children.get_mut(&offset) {
@@ -633,13 +1001,19 @@ impl Vmar {
         // Update protection in VMAR
         self.protect(offset, size, new_prot)?;
 
+        // Keep the memory type the region was mapped with
+        let cache_policy = match self.children.read().get(&offset) {
+            Some(VmarRegion::Mapping { cache_policy, .. }) => *cache_policy,
+            _ => vmo::CachePolicy::Default,
+        };
+
         // Calculate the virtual address
         let vaddr = (self.base + offset) as usize;
 
         // Update protection in address space
         let page_count = (size / PAGE_SIZE as u64) as usize;
         if page_count > 0 {
-            if let Err(err) = aspace.protect(vaddr, page_count, new_prot) {
+            if let Err(err) = aspace.protect_with_cache_policy(vaddr, page_count, new_prot, cache_policy) {
                 log_error!("VMAR: Failed to update protections: {:?}", err);
                 return Err(err as i32);
             }
@@ -761,6 +1135,18 @@ fn get_root_user_vmar() -> Option<&'static Arc<Vmar>> {
     unsafe { ROOT_USER_VMAR.as_ref() }
 }
 
+/// Every mapping in the user address space
+pub(crate) fn user_mappings() -> Vec<VmarMapping> {
+    get_root_user_vmar().map_or_else(Vec::new, |root| root.mappings())
+}
+
+/// The VMAR user address `addr` is resolved in
+///
+/// All processes share the root user VMAR for now.
+pub(crate) fn user_vmar() -> Result<Arc<Vmar>> {
+    get_root_user_vmar().cloned().ok_or(RX_ERR_NOT_SUPPORTED)
+}
+
 /// ============================================================================
 /// Handle to VMAR Resolution
 /// ============================================================================
@@ -783,7 +1169,12 @@ fn lookup_vmar_from_handle(
     VMAR_REGISTRY
         .lock()
         .get(vmar_id)
-        .ok_or(RX_ERR_INVALID_ARGS)
+        .ok_or(RX_ERR_BAD_HANDLE)
+}
+
+/// Look up a VMAR for inspection, as by `rx_object_get_info`
+pub(crate) fn lookup_vmar(handle_val: u32) -> Result<Arc<Vmar>> {
+    lookup_vmar_from_handle(handle_val, Rights::READ)
 }
 
 /// ============================================================================
@@ -908,15 +1299,24 @@ pub fn sys_vmar_allocate_impl(
 /// Syscall: VMAR Map
 /// ============================================================================
 
-/// Look up a VMO from a handle
-fn lookup_vmo_from_handle(handle_val: u32, _required_rights: Rights) -> Option<Arc<Vmo>> {
-    // TODO: Integrate with proper VMO handle table lookup
-    // For now, return None to indicate not implemented
-    // In a real implementation, this would:
-    // 1. Look up the handle in the current process's handle table
-    // 2. Validate it's a VMO handle with the required rights
-    // 3. Return the VMO object
-    None
+/// Cache policy for a new mapping of `vmo`
+///
+/// The VMO's own policy, unless `options` selects `MAP_UNCACHED` or
+/// `MAP_WRITE_COMBINING`. Paged VMOs are also reachable through the kernel's
+/// cached physmap, so only physical VMOs may be mapped with a different
+/// policy; paged VMOs use `rx_vmo_set_cache_policy` instead.
+fn mapping_cache_policy(vmo: &Vmo, options: u32) -> Result<vmo::CachePolicy> {
+    let policy = match options & vmar_options::CACHE_FLAGS {
+        0 => return Ok(vmo.cache_policy()),
+        vmar_options::MAP_UNCACHED => vmo::CachePolicy::Uncached,
+        vmar_options::MAP_WRITE_COMBINING => vmo::CachePolicy::WriteCombining,
+        _ => return Err(RX_ERR_INVALID_ARGS),
+    };
+
+    if !vmo.flags.is_physical() {
+        return Err(RX_ERR_INVALID_ARGS);
+    }
+    Ok(policy)
 }
 
 /// Map a VMO into a VMAR syscall handler
@@ -924,8 +1324,10 @@ fn lookup_vmo_from_handle(handle_val: u32, _required_rights: Rights) -> Option<A
 /// # Arguments
 ///
 /// * `vmar_handle` - VMAR handle
-/// * `options` - Mapping options (permissions, etc.)
-/// * `vmar_offset` - Offset within VMAR
+/// * `options` - Mapping options (permissions, placement, alignment); see
+///   `vmar_options::check_map` for the invalid combinations
+/// * `vmar_offset` - Offset within VMAR with SPECIFIC, upper limit with
+///   OFFSET_IS_UPPER_LIMIT, otherwise 0
 /// * `vmo_handle` - VMO handle to map
 /// * `vmo_offset` - Offset within VMO
 /// * `len` - Length to map
@@ -961,12 +1363,12 @@ pub fn sys_vmar_map_impl(
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // Validate protection flags
-    let perm_flags = options & vmar_options::PERM_FLAGS;
-    if perm_flags & !vmar_options::PERM_FLAGS != 0 {
-        log_error!("sys_vmar_map: invalid permission flags");
-        return err_to_ret(RX_ERR_INVALID_ARGS);
+    // Validate options before touching any objects
+    if let Err(err) = vmar_options::check_map(options, vmar_offset, vmo_offset) {
+        log_error!("sys_vmar_map: invalid options {:#x}", options);
+        return err_to_ret(err);
     }
+    let perm_flags = options & vmar_options::PERM_FLAGS;
 
     // Look up VMAR
     let vmar = match lookup_vmar_from_handle(vmar_handle, Rights::WRITE) {
@@ -977,16 +1379,36 @@ pub fn sys_vmar_map_impl(
         }
     };
 
+    // Enforce W^X and the VMAR's CAN_MAP_* capabilities
+    if !vmar.can_map(perm_flags) {
+        log_error!("sys_vmar_map: permissions {:#x} not allowed in this VMAR", perm_flags);
+        return err_to_ret(RX_ERR_ACCESS_DENIED);
+    }
+
     // Convert options to protection
     let prot = vmar_options::perm_to_prot(options);
-    let cache_policy = vmo::CachePolicy::Default;
-
-    // Look up VMO from handle
-    let vmo = match lookup_vmo_from_handle(vmo_handle, Rights::READ) {
-        Some(v) => v,
-        None => {
-            log_error!("sys_vmar_map: VMO lookup not implemented");
-            return err_to_ret(RX_ERR_NOT_SUPPORTED);
+
+    // Look up VMO from handle: mapping needs MAP plus the access mapped
+    let mut vmo_rights = Rights::MAP | Rights::READ;
+    if perm_flags & vmar_options::PERM_WRITE != 0 {
+        vmo_rights = vmo_rights.add(Rights::WRITE);
+    }
+    if perm_flags & vmar_options::PERM_EXECUTE != 0 {
+        vmo_rights = vmo_rights.add(Rights::EXECUTE);
+    }
+    let vmo = match super::vmo::lookup_vmo_from_handle(vmo_handle, vmo_rights) {
+        Ok((v, _handle)) => v,
+        Err(err) => {
+            log_error!("sys_vmar_map: bad VMO handle: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    let cache_policy = match mapping_cache_policy(&vmo, options) {
+        Ok(policy) => policy,
+        Err(err) => {
+            log_error!("sys_vmar_map: invalid cache options {:#x}", options & vmar_options::CACHE_FLAGS);
+            return err_to_ret(err);
         }
     };
 
@@ -1025,6 +1447,7 @@ pub fn sys_vmar_map_impl(
     }
 
     log_debug!("sys_vmar_map: success addr={:#x}", mapped_addr);
+    ktrace::write(ktrace::TAG_VMAR_MAP, 0, mapped_addr as u64, len);
 
     ok_to_ret(mapped_addr as usize)
 }
@@ -1079,6 +1502,7 @@ pub fn sys_vmar_unmap_impl(vmar_handle: u32, addr: u64, len: u64) -> SyscallRet
     }
 
     log_debug!("sys_vmar_unmap: success");
+    ktrace::write(ktrace::TAG_VMAR_UNMAP, 0, addr, len);
 
     ok_to_ret(0)
 }
@@ -1125,9 +1549,6 @@ pub fn sys_vmar_protect_impl(vmar_handle: u32, options: u32, addr: u64, len: u64
         return err_to_ret(RX_ERR_INVALID_ARGS);
     }
 
-    // Convert options to protection
-    let prot = vmar_options::perm_to_prot(options);
-
     // Look up VMAR
     let vmar = match lookup_vmar_from_handle(vmar_handle, Rights::WRITE) {
         Ok(v) => v,
@@ -1137,6 +1558,15 @@ pub fn sys_vmar_protect_impl(vmar_handle: u32, options: u32, addr: u64, len: u64
         }
     };
 
+    // Enforce W^X and the VMAR's CAN_MAP_* capabilities
+    if !vmar.can_map(perm_flags) {
+        log_error!("sys_vmar_protect: permissions {:#x} not allowed in this VMAR", perm_flags);
+        return err_to_ret(RX_ERR_ACCESS_DENIED);
+    }
+
+    // Convert options to protection
+    let prot = vmar_options::perm_to_prot(options);
+
     // Perform the protect operation
     if let Err(err) = vmar.protect(addr, len, prot) {
         log_error!("sys_vmar_protect: protect failed: {:?}", err);
@@ -1301,6 +1731,50 @@ mod tests {
         assert_eq!(addr, Some(0x1000));
     }
 
+    #[test]
+    fn test_vmar_find_free_region_from_hint() {
+        let mut children = BTreeMap::new();
+        children.insert(0x4000, VmarRegion::Vmar { vmar: Vmar::new_root(0x4000, 0x2000) });
+
+        // Starting inside a region skips past it
+        assert_eq!(Vmar::find_free_region_from(&children, 0x5000, 0x10000, 0x1000, 0x1000), Some(0x6000));
+
+        // Unaligned hints are rounded up
+        assert_eq!(Vmar::find_free_region_from(&children, 0x1234, 0x10000, 0x1000, 0x1000), Some(0x2000));
+
+        // A gap too small for the request is skipped
+        assert_eq!(Vmar::find_free_region_from(&children, 0x3000, 0x10000, 0x2000, 0x1000), Some(0x6000));
+
+        // Nothing fits above the hint
+        assert_eq!(Vmar::find_free_region_from(&children, 0xF000, 0x10000, 0x2000, 0x1000), None);
+    }
+
+    #[test]
+    fn test_vmar_wxorx() {
+        let rw = vmar_options::PERM_READ | vmar_options::PERM_WRITE;
+        let rx = vmar_options::PERM_READ | vmar_options::PERM_EXECUTE;
+        let rwx = rw | vmar_options::PERM_EXECUTE;
+
+        // The root VMAR allows every permission, but never W+X together
+        let root = Vmar::new_root(0x1000, 0x100000);
+        assert!(root.can_map(rw));
+        assert!(root.can_map(rx));
+        assert!(!root.can_map(rwx));
+        assert!(!root.can_map(vmar_options::PERM_WRITE | vmar_options::PERM_EXECUTE));
+
+        // A child needs the explicit override
+        let caps = vmar_options::CAN_MAP_READ | vmar_options::CAN_MAP_WRITE | vmar_options::CAN_MAP_EXECUTE;
+        let child = Vmar::new_child(&root, 0x10000, 0x1000, caps, 0xFFF).unwrap();
+        assert!(!child.can_map(rwx));
+
+        let jit = Vmar::new_child(&root, 0x20000, 0x1000, caps | vmar_options::CAN_MAP_WRITE_EXECUTE, 0xFFF).unwrap();
+        assert!(jit.can_map(rwx));
+
+        // The override is meaningless without both WRITE and EXECUTE capabilities
+        let flags = VmarFlags::from_options(vmar_options::CAN_MAP_WRITE | vmar_options::CAN_MAP_WRITE_EXECUTE, false);
+        assert!(!flags.can_map_write_execute);
+    }
+
     #[test]
     fn test_vmar_allocate_validation() {
         // Invalid: zero size
@@ -1310,6 +1784,159 @@ mod tests {
         assert!(sys_vmar_allocate_impl(0, 0, 0, 0x1001, 0) < 0);
     }
 
+    #[test]
+    fn test_vmar_map_cache_policy() {
+        let physical = Arc::new(Vmo::create_physical(0x1000_0000, 0x2000).unwrap());
+        let paged = Vmo::create(0x1000, vmo::VmoFlags::empty).unwrap();
+
+        // Mappings take the VMO's policy unless a physical VMO overrides it
+        assert_eq!(mapping_cache_policy(&physical, 0), Ok(vmo::CachePolicy::Uncached));
+        assert_eq!(
+            mapping_cache_policy(&physical, vmar_options::MAP_WRITE_COMBINING),
+            Ok(vmo::CachePolicy::WriteCombining)
+        );
+        assert_eq!(mapping_cache_policy(&paged, 0), Ok(vmo::CachePolicy::Default));
+        assert_eq!(
+            mapping_cache_policy(&paged, vmar_options::MAP_UNCACHED),
+            Err(RX_ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            mapping_cache_policy(&physical, vmar_options::CACHE_FLAGS),
+            Err(RX_ERR_INVALID_ARGS)
+        );
+
+        // The policy is fixed while the VMO is mapped
+        let root = Vmar::new_root(0x1000, 0x100000);
+        let offset = root
+            .map(physical.clone(), 0, 0, 0x2000, MemProt::ReadWrite, vmo::CachePolicy::WriteCombining, 0)
+            .unwrap();
+        assert_eq!(physical.mapping_count(), 1);
+        assert_eq!(physical.set_cache_policy(vmo::CachePolicy::Default), Err(RX_ERR_BAD_STATE));
+
+        root.unmap(offset, 0x2000).unwrap();
+        assert_eq!(physical.mapping_count(), 0);
+        assert!(physical.set_cache_policy(vmo::CachePolicy::WriteCombining).is_ok());
+    }
+
+    #[test]
+    fn test_vmar_map_options() {
+        use vmar_options::*;
+
+        assert_eq!(alignment(0), Some(0x1000));
+        assert_eq!(alignment(ALIGN_2MB), Some(0x20_0000));
+        assert_eq!(alignment(ALIGN_4GB), Some(0x1_0000_0000));
+        assert_eq!(alignment(11 << ALIGN_SHIFT), None);
+        assert_eq!(alignment(33 << ALIGN_SHIFT), None);
+
+        assert_eq!(check_map(PERM_READ, 0, 0), Ok(0x1000));
+        assert_eq!(check_map(PERM_READ | SPECIFIC | ALIGN_2MB, 0x40_0000, 0), Ok(0x20_0000));
+        assert_eq!(check_map(OFFSET_IS_UPPER_LIMIT | COMPACT, 0x12_3000, 0), Ok(0x1000));
+
+        // Unknown and allocate-only bits
+        assert_eq!(check_map(0x80, 0, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(check_map(CAN_MAP_READ, 0, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(check_map(1 << 30, 0, 0), Err(RX_ERR_INVALID_ARGS));
+
+        // Conflicting placements
+        assert_eq!(check_map(SPECIFIC | OFFSET_IS_UPPER_LIMIT, 0x1000, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(check_map(SPECIFIC_OVERWRITE | COMPACT, 0x1000, 0), Err(RX_ERR_INVALID_ARGS));
+
+        // An offset must be an address or a limit
+        assert_eq!(check_map(PERM_READ, 0x1000, 0), Err(RX_ERR_INVALID_ARGS));
+
+        // Misaligned offsets
+        assert_eq!(check_map(SPECIFIC | ALIGN_2MB, 0x1000, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(check_map(SPECIFIC, 0x1800, 0), Err(RX_ERR_INVALID_ARGS));
+        assert_eq!(check_map(PERM_READ, 0, 0x800), Err(RX_ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_vmar_map_placement() {
+        let vmo = Arc::new(Vmo::create(0x2000, vmo::VmoFlags::empty).unwrap());
+        let root = Vmar::new_root(0x1000, 0x100_0000);
+        let map = |offset, options| {
+            root.map(vmo.clone(), offset, 0, 0x1000, MemProt::Read, vmo::CachePolicy::Default, options)
+        };
+
+        // Alignment is honored past existing mappings
+        assert_eq!(map(0, vmar_options::COMPACT), Ok(0));
+        assert_eq!(map(0, vmar_options::COMPACT | vmar_options::ALIGN_2MB), Ok(0x20_0000));
+
+        // The upper limit bounds the search
+        assert_eq!(map(0x3000, vmar_options::OFFSET_IS_UPPER_LIMIT | vmar_options::COMPACT), Ok(0x1000));
+        assert_eq!(map(0x2000, vmar_options::OFFSET_IS_UPPER_LIMIT), Err(RX_ERR_NO_RESOURCES));
+
+        // SPECIFIC fails on overlap, SPECIFIC_OVERWRITE replaces the mapping
+        assert_eq!(map(0x1000, vmar_options::SPECIFIC), Err(RX_ERR_ALREADY_EXISTS));
+        assert_eq!(map(0x1000, vmar_options::SPECIFIC_OVERWRITE), Ok(0x1000));
+        assert_eq!(root.children.read().len(), 3);
+
+        // A specific address must fit in the VMAR
+        assert_eq!(map(0x100_0000, vmar_options::SPECIFIC), Err(RX_ERR_INVALID_ARGS));
+
+        // Child VMARs are never overwritten
+        let _child = Vmar::new_child(&root, 0x40_0000, 0x1000, 0, 0).unwrap();
+        assert_eq!(map(0x40_0000, vmar_options::SPECIFIC_OVERWRITE), Err(RX_ERR_ALREADY_EXISTS));
+    }
+
+    #[test]
+    fn test_vmar_lookup() {
+        let vmo = Arc::new(Vmo::create(0x4000, vmo::VmoFlags::empty).unwrap());
+        let root = Vmar::new_root(0x1000, 0x10_0000);
+        root.map(vmo.clone(), 0x2000, 0x1000, 0x2000, MemProt::Read, vmo::CachePolicy::Default, vmar_options::SPECIFIC)
+            .unwrap();
+        let child = Vmar::new_child(&root, 0x8000, 0x4000, vmar_options::CAN_MAP_READ, 0).unwrap();
+        child
+            .map(vmo.clone(), 0x1000, 0x3000, 0x1000, MemProt::Read, vmo::CachePolicy::Default, vmar_options::SPECIFIC)
+            .unwrap();
+
+        let found = |offset| root.lookup(offset).map(|(_, vmo_offset, prot)| (vmo_offset, prot));
+        assert_eq!(found(0x1fff), None);
+        assert_eq!(found(0x2000), Some((0x1000, MemProt::Read)));
+        assert_eq!(found(0x3abc), Some((0x2abc, MemProt::Read)));
+        assert_eq!(found(0x4000), None);
+
+        // Through the child VMAR
+        assert_eq!(found(0x8000), None);
+        assert_eq!(found(0x9010), Some((0x3010, MemProt::Read)));
+    }
+
+    #[test]
+    fn test_vmar_mappings() {
+        let vmo = Arc::new(Vmo::create(0x4000, vmo::VmoFlags::empty).unwrap());
+        let root = Vmar::new_root(0x1000, 0x10_0000);
+        root.map(vmo.clone(), 0x2000, 0x1000, 0x2000, MemProt::Read, vmo::CachePolicy::Default, vmar_options::SPECIFIC)
+            .unwrap();
+        let child = Vmar::new_child(&root, 0x8000, 0x4000, vmar_options::CAN_MAP_READ, 0).unwrap();
+        child
+            .map(vmo.clone(), 0x1000, 0x3000, 0x1000, MemProt::Read, vmo::CachePolicy::Default, vmar_options::SPECIFIC)
+            .unwrap();
+
+        let mappings: Vec<_> = root.mappings().iter().map(|m| (m.addr, m.size, m.vmo_offset)).collect();
+        assert_eq!(mappings, [(0x2000, 0x2000, 0x1000), (0x9000, 0x1000, 0x3000)]);
+    }
+
+    #[test]
+    fn test_vmar_access_memory() {
+        let vmo = Arc::new(Vmo::create(0x4000, vmo::VmoFlags::empty).unwrap());
+        let root = Vmar::new_root(0x1000, 0x10_0000);
+        root.map(vmo.clone(), 0x2000, 0x1000, 0x2000, MemProt::Read, vmo::CachePolicy::Default, vmar_options::SPECIFIC)
+            .unwrap();
+
+        // Across a page boundary, and read-only mappings are written too
+        assert_eq!(root.write_memory(0x2ffe, &[1, 2, 3, 4]), Ok(4));
+        let mut buf = [0u8; 4];
+        assert_eq!(root.read_memory(0x2ffe, &mut buf), Ok(4));
+        assert_eq!(buf, [1, 2, 3, 4]);
+        assert_eq!(vmo.read(0x1ffe, &mut buf), Ok(4));
+        assert_eq!(buf, [1, 2, 3, 4]);
+
+        // Stops at the end of the mapping
+        let mut buf = [0u8; 8];
+        assert_eq!(root.read_memory(0x3ffc, &mut buf), Ok(4));
+        assert_eq!(root.read_memory(0x4000, &mut buf), Ok(0));
+    }
+
     #[test]
     fn test_vmar_map_validation() {
         // Invalid: zero length
@@ -1317,6 +1944,12 @@ mod tests {
 
         // Invalid: non-page-aligned length
         assert!(sys_vmar_map_impl(0, 0, 0, 0, 0, 0x1001, 0) < 0);
+
+        // Invalid options are rejected before the handles are looked up
+        assert_eq!(
+            sys_vmar_map_impl(0, vmar_options::SPECIFIC | vmar_options::COMPACT, 0, 0, 0, 0x1000, 0),
+            err_to_ret(RX_ERR_INVALID_ARGS)
+        );
     }
 
     #[test]
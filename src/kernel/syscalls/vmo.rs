@@ -16,6 +16,12 @@
 //! - `rx_vmo_read` - Read from a VMO
 //! - `rx_vmo_write` - Write to a VMO
 //! - `rx_vmo_clone` - Clone a VMO (COW)
+//! - `rx_vmo_set_size` - Resize a VMO
+//! - `rx_vmo_op_range` - Commit, decommit, zero or cache-maintain a range,
+//!   or lock a discardable VMO
+//! - `rx_vmo_set_cache_policy` - Set the cache policy of later mappings
+//! - `rx_vmo_transfer_data` - Move pages from one VMO to another without
+//!   copying
 //!
 //! # Design
 //!
@@ -25,15 +31,18 @@
 //! - User pointers are validated before access
 
 
+use crate::kernel::object::koid::Koid;
 use crate::kernel::object::vmo::{self, Vmo, VmoFlags};
 use crate::kernel::object::{Handle, HandleTable, KernelObjectBase, ObjectType, Rights};
 use crate::kernel::sync::Mutex;
 use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserPtr};
 use crate::kernel::vm::layout::*;
+use crate::kernel::syscalls::object_wait;
 use crate::kernel::syscalls::{SyscallRet, err_to_ret, ok_to_ret};
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 
 // Import logging macros
@@ -47,6 +56,12 @@ use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 /// Maximum number of VMOs in the system
 const MAX_VMOS: usize = 65536;
 
+/// Rights of a handle to a new VMO
+const VMO_DEFAULT_RIGHTS: Rights = Rights::DEFAULT.add(Rights::DUPLICATE).add(Rights::TRANSFER);
+
+/// Rights of a handle to a new resizable VMO
+const VMO_RESIZABLE_RIGHTS: Rights = VMO_DEFAULT_RIGHTS.add(Rights::RESIZE);
+
 /// VMO registry entry
 struct VmoEntry {
     /// VMO ID
@@ -54,15 +69,22 @@ struct VmoEntry {
 
     /// VMO object
     vmo: Arc<Vmo>,
+
+    /// Object base the VMO's handles point at
+    base: Box<KernelObjectBase>,
 }
 
 /// Global VMO registry
 ///
-/// Maps VMO IDs to VMO objects. This is used to resolve handles to VMOs.
+/// Maps VMO IDs to VMO objects. Handles name a VMO by koid, so the
+/// registry also indexes VMOs by koid.
 struct VmoRegistry {
     /// VMO entries
     entries: [Option<VmoEntry>; MAX_VMOS],
 
+    /// VMO IDs by koid
+    koids: BTreeMap<Koid, vmo::VmoId>,
+
     /// Next VMO index to allocate
     next_index: AtomicUsize,
 
@@ -77,6 +99,7 @@ impl VmoRegistry {
 
         Self {
             entries: [INIT; MAX_VMOS],
+            koids: BTreeMap::new(),
             next_index: AtomicUsize::new(0),
             count: AtomicUsize::new(0),
         }
@@ -93,7 +116,9 @@ impl VmoRegistry {
         loop {
             // Try to allocate at current index
             if self.entries[idx].is_none() {
-                self.entries[idx] = Some(VmoEntry { id, vmo });
+                let base = Box::new(KernelObjectBase::with_koid(ObjectType::Vmo, vmo.koid));
+                self.koids.insert(vmo.koid, id);
+                self.entries[idx] = Some(VmoEntry { id, vmo, base });
                 self.count.fetch_add(1, Ordering::Relaxed);
                 self.next_index.store((idx + 1) % MAX_VMOS, Ordering::Relaxed);
                 return Ok(id);
@@ -118,12 +143,28 @@ impl VmoRegistry {
             .map(|entry| entry.vmo.clone())
     }
 
+    /// Get the VMO with kernel object ID `koid`
+    pub fn get_by_koid(&self, koid: Koid) -> Option<Arc<Vmo>> {
+        self.koids.get(&koid).and_then(|&id| self.get(id))
+    }
+
+    /// Make a handle with `rights` to a registered VMO
+    pub fn handle(&self, id: vmo::VmoId, rights: Rights) -> Option<Handle> {
+        let idx = (id as usize) % MAX_VMOS;
+
+        self.entries[idx]
+            .as_ref()
+            .filter(|entry| entry.id == id)
+            .map(|entry| Handle::new(&*entry.base as *const KernelObjectBase, rights))
+    }
+
     /// Remove a VMO from the registry
     pub fn remove(&mut self, id: vmo::VmoId) -> Option<Arc<Vmo>> {
         let idx = (id as usize) % MAX_VMOS;
 
         if let Some(entry) = self.entries[idx].take() {
             if entry.id == id {
+                self.koids.remove(&entry.vmo.koid);
                 self.count.fetch_sub(1, Ordering::Relaxed);
                 return Some(entry.vmo);
             }
@@ -136,6 +177,11 @@ impl VmoRegistry {
     pub fn count(&self) -> usize {
         self.count.load(Ordering::Relaxed)
     }
+
+    /// Iterate over the registered VMOs
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Vmo>> {
+        self.entries.iter().flatten().map(|entry| &entry.vmo)
+    }
 }
 
 /// Global VMO registry
@@ -147,13 +193,10 @@ static VMO_REGISTRY: Mutex<VmoRegistry> = Mutex::new(VmoRegistry::new());
 
 /// Get the current process's handle table
 ///
-/// This is a placeholder that returns NULL for now.
-/// In a real implementation, this would use thread-local storage
-/// or per-CPU data to get the current process.
+/// Uses the same table as the handle syscalls until processes are
+/// tracked per thread.
 fn current_process_handle_table() -> Option<&'static HandleTable> {
-    // TODO: Implement proper current process tracking
-    // For now, return None to indicate not implemented
-    None
+    Some(crate::kernel::thread::current_thread_handle_table())
 }
 
 /// Look up a VMO from a handle value
@@ -163,7 +206,7 @@ fn current_process_handle_table() -> Option<&'static HandleTable> {
 /// 2. Looks up the handle in the table
 /// 3. Validates the handle type and rights
 /// 4. Returns the VMO object
-fn lookup_vmo_from_handle(
+pub(crate) fn lookup_vmo_from_handle(
     handle_val: u32,
     required_rights: Rights,
 ) -> Result<(Arc<Vmo>, Handle)> {
@@ -173,7 +216,7 @@ fn lookup_vmo_from_handle(
 
     // Get the handle from the table
     let handle = handle_table.get(handle_val)
-        .ok_or(RX_ERR_INVALID_ARGS)?;
+        .ok_or(RX_ERR_BAD_HANDLE)?;
 
     // Validate object type
     if handle.obj_type() != ObjectType::Vmo {
@@ -183,37 +226,99 @@ fn lookup_vmo_from_handle(
     // Validate rights
     handle.require(required_rights)?;
 
-    // Get VMO ID from handle (stored as part of base pointer for now)
-    // In a real implementation, the handle would store the VMO ID directly
-    let vmo_id = handle.id as vmo::VmoId;
-
     // Get VMO from registry
-    let vmo = VMO_REGISTRY.lock().get(vmo_id)
-        .ok_or(RX_ERR_NOT_FOUND)?;
+    let vmo = VMO_REGISTRY.lock().get_by_koid(handle.koid())
+        .ok_or(RX_ERR_BAD_HANDLE)?;
 
     Ok((vmo, handle))
 }
 
-/// ============================================================================
-/// VMO Kernel Object Base
-/// ============================================================================
+/// Register `vmo` and add a handle to it to the current process's handle
+/// table, returning the handle value
+///
+/// The handle gets the default VMO rights, plus `RESIZE` if the VMO is
+/// resizable.
+fn install_vmo(vmo: Arc<Vmo>) -> Result<u32> {
+    let rights = if vmo.flags.is_resizable() { VMO_RESIZABLE_RIGHTS } else { VMO_DEFAULT_RIGHTS };
+    let handle_table = current_process_handle_table()
+        .ok_or(RX_ERR_NOT_SUPPORTED)?;
+
+    let (vmo_id, handle) = {
+        let mut registry = VMO_REGISTRY.lock();
+        let vmo_id = registry.insert(vmo)?;
+        (vmo_id, registry.handle(vmo_id, rights).ok_or(RX_ERR_INTERNAL)?)
+    };
 
-/// Create a kernel object base for a VMO
-fn vmo_to_kernel_base(vmo: &Arc<Vmo>) -> KernelObjectBase {
-    KernelObjectBase::new(ObjectType::Vmo)
+    handle_table.add(handle).map_err(|err| {
+        VMO_REGISTRY.lock().remove(vmo_id);
+        err
+    })
+}
+
+/// Register a kernel-created VMO and return its handle value
+///
+/// Used by subsystems that hand VMOs to userspace, such as PCI BARs.
+pub(crate) fn register_vmo(vmo: Vmo) -> Result<u32> {
+    register_shared_vmo(Arc::new(vmo))
+}
+
+/// Register a VMO the kernel keeps using and return its handle value
+///
+/// Used to hand over buffers the kernel writes, such as trace buffers.
+pub(crate) fn register_shared_vmo(vmo: Arc<Vmo>) -> Result<u32> {
+    install_vmo(vmo)
+}
+
+/// Discard unlocked discardable VMOs until `target_pages` pages are freed
+///
+/// Called by memory reclaim. Each VMO that loses its pages has
+/// `VMO_DISCARDED` asserted, waking its waiters. Returns the number of
+/// pages freed.
+pub fn reclaim_discardable(target_pages: usize) -> usize {
+    let mut freed = 0;
+    for vmo in VMO_REGISTRY.lock().iter() {
+        if freed >= target_pages {
+            break;
+        }
+        let pages = vmo.discard();
+        if pages != 0 {
+            signal_vmo_waiters(vmo, object_wait::signal::VMO_DISCARDED);
+            freed += pages;
+        }
+    }
+    freed
+}
+
+/// Wake the threads waiting for `signals` on any handle to `vmo`
+fn signal_vmo_waiters(vmo: &Vmo, signals: u64) {
+    let Some(handle_table) = current_process_handle_table() else {
+        return;
+    };
+    for handle_val in handle_table.handle_values() {
+        if handle_table.get(handle_val).is_some_and(|handle| handle.koid() == vmo.koid) {
+            object_wait::wake_waiters(handle_val, signals);
+        }
+    }
 }
 
 /// ============================================================================
 /// Syscall: VMO Create
 /// ============================================================================
 
+/// `rx_vmo_create` option: the VMO's pages may be discarded while unlocked
+pub const VMO_CREATE_DISCARDABLE: u32 = 0x04;
+
+/// The only valid use of `VMO_CREATE_DISCARDABLE`
+const VMO_CREATE_NON_RESIZABLE_DISCARDABLE: u32 = 1 | VMO_CREATE_DISCARDABLE;
+
 /// Create a new VMO syscall handler
 ///
 /// # Arguments
 ///
 /// * `args` - Syscall arguments
 ///   - args[0]: Size in bytes (must be page-aligned)
-///   - args[1]: Options (0 for resizable, 1 for non-resizable)
+///   - args[1]: Options (0 for resizable, 1 for non-resizable), optionally
+///     with `VMO_CREATE_DISCARDABLE`, which needs non-resizable
 ///
 /// # Returns
 ///
@@ -232,6 +337,7 @@ pub fn sys_vmo_create_impl(size: usize, options: u32) -> SyscallRet {
     let flags = match options {
         0 => VmoFlags::RESIZABLE,
         1 => VmoFlags::empty,
+        VMO_CREATE_NON_RESIZABLE_DISCARDABLE => VmoFlags::DISCARDABLE,
         _ => {
             log_error!("sys_vmo_create: invalid options {}", options);
             return err_to_ret(RX_ERR_INVALID_ARGS);
@@ -249,29 +355,15 @@ pub fn sys_vmo_create_impl(size: usize, options: u32) -> SyscallRet {
 
     log_debug!("sys_vmo_create: created VMO id={}", vmo.id);
 
-    // Wrap in Arc for registry
-    let vmo_arc = Arc::new(vmo);
-
-    // Insert into VMO registry
-    let vmo_id = match VMO_REGISTRY.lock().insert(vmo_arc.clone()) {
-        Ok(id) => id,
+    // Register the VMO and add a handle to it
+    let handle_value = match install_vmo(Arc::new(vmo)) {
+        Ok(value) => value,
         Err(err) => {
-            log_error!("sys_vmo_create: failed to insert VMO into registry: {:?}", err);
+            log_error!("sys_vmo_create: failed to install VMO handle: {:?}", err);
             return err_to_ret(err);
         }
     };
 
-    // Create kernel object base
-    let base = vmo_to_kernel_base(&vmo_arc);
-
-    // Create handle with default rights
-    let rights = Rights::DEFAULT;
-    let handle = Handle::new(&base as *const KernelObjectBase, rights);
-
-    // TODO: Add handle to current process's handle table
-    // For now, return the VMO ID as the handle value
-    let handle_value = vmo_id as u32;
-
     log_debug!("sys_vmo_create: success handle={}", handle_value);
 
     ok_to_ret(handle_value as usize)
@@ -495,33 +587,237 @@ pub fn sys_vmo_clone_impl(
 
     log_debug!("sys_vmo_clone: created VMO clone id={}", vmo.id);
 
-    // Wrap in Arc for registry
-    let vmo_arc = Arc::new(vmo);
+    // Register the clone and add a handle to it; the default rights
+    // include WRITE, as COW clones are writable
+    let handle_value = match install_vmo(Arc::new(vmo)) {
+        Ok(value) => value,
+        Err(err) => {
+            log_error!("sys_vmo_clone: failed to install VMO handle: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    log_debug!("sys_vmo_clone: success handle={}", handle_value);
+
+    ok_to_ret(handle_value as usize)
+}
+
+/// ============================================================================
+/// Syscall: VMO Set Size
+/// ============================================================================
+
+/// Resize a VMO syscall handler
+///
+/// Shrinking frees the pages past the new end and removes them from every
+/// mapping.
+///
+/// # Arguments
+///
+/// * `handle_val` - VMO handle
+/// * `size` - New size in bytes (must be page-aligned)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code; `RX_ERR_NOT_SUPPORTED` if the VMO isn't
+///   resizable
+pub fn sys_vmo_set_size_impl(handle_val: u32, size: usize) -> SyscallRet {
+    log_debug!("sys_vmo_set_size: handle={} size={:#x}", handle_val, size);
+
+    if (size & 0xFFF) != 0 {
+        log_error!("sys_vmo_set_size: invalid size {:#x}", size);
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
 
-    // Insert into VMO registry
-    let vmo_id = match VMO_REGISTRY.lock().insert(vmo_arc.clone()) {
-        Ok(id) => id,
+    let (vmo, _handle) = match lookup_vmo_from_handle(handle_val, Rights::WRITE | Rights::RESIZE) {
+        Ok(vmo) => vmo,
         Err(err) => {
-            log_error!("sys_vmo_clone: failed to insert VMO into registry: {:?}", err);
+            log_error!("sys_vmo_set_size: failed to lookup VMO: {:?}", err);
             return err_to_ret(err);
         }
     };
 
-    // Create kernel object base
-    let base = vmo_to_kernel_base(&vmo_arc);
+    match vmo.resize(size) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
 
-    // Create handle with default rights (add WRITE for COW clones)
-    let mut rights = Rights::DEFAULT;
-    rights = rights.add(Rights::WRITE); // COW clones are writable
-    let handle = Handle::new(&base as *const KernelObjectBase, rights);
+/// ============================================================================
+/// Syscall: VMO Op Range
+/// ============================================================================
 
-    // TODO: Add handle to current process's handle table
-    // For now, return the VMO ID as the handle value
-    let handle_value = vmo_id as u32;
+/// Rights a handle needs to run `op`
+///
+/// Operations that can change what the VMO reads as need `WRITE`; the
+/// rest only need `READ`.
+fn op_rights(op: vmo::VmoOp) -> Rights {
+    match op {
+        vmo::VmoOp::Commit
+        | vmo::VmoOp::Decommit
+        | vmo::VmoOp::Zero
+        | vmo::VmoOp::CacheInvalidate => Rights::WRITE,
+        vmo::VmoOp::CacheClean
+        | vmo::VmoOp::CacheCleanInvalidate
+        | vmo::VmoOp::Lock
+        | vmo::VmoOp::TryLock
+        | vmo::VmoOp::Unlock => Rights::READ,
+    }
+}
 
-    log_debug!("sys_vmo_clone: success handle={}", handle_value);
+/// VMO range operation syscall handler
+///
+/// # Arguments
+///
+/// * `handle_val` - VMO handle
+/// * `op` - Raw `VmoOp` value
+/// * `offset` - Start of the range in bytes
+/// * `size` - Length of the range in bytes
+///
+/// # Returns
+///
+/// * On success: 0; for `Lock`, 1 if the pages were discarded since the
+///   last lock
+/// * On error: Negative error code; `RX_ERR_NOT_FOUND` for a `TryLock` of
+///   a VMO whose pages were discarded
+pub fn sys_vmo_op_range_impl(handle_val: u32, op: u32, offset: usize, size: usize) -> SyscallRet {
+    log_debug!(
+        "sys_vmo_op_range: handle={} op={} offset={:#x} size={:#x}",
+        handle_val, op, offset, size
+    );
 
-    ok_to_ret(handle_value as usize)
+    let op = match vmo::VmoOp::from_raw(op) {
+        Some(op) => op,
+        None => {
+            log_error!("sys_vmo_op_range: invalid op {}", op);
+            return err_to_ret(RX_ERR_INVALID_ARGS);
+        }
+    };
+
+    let (vmo, _handle) = match lookup_vmo_from_handle(handle_val, op_rights(op)) {
+        Ok(vmo) => vmo,
+        Err(err) => {
+            log_error!("sys_vmo_op_range: failed to lookup VMO: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    if op == vmo::VmoOp::Lock {
+        if offset != 0 || size != vmo.size() {
+            return err_to_ret(RX_ERR_INVALID_ARGS);
+        }
+        return match vmo.lock() {
+            Ok(discarded) => ok_to_ret(discarded as usize),
+            Err(err) => err_to_ret(err),
+        };
+    }
+
+    match vmo.op_range(op, offset, size) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: VMO Set Cache Policy
+/// ============================================================================
+
+/// Set a VMO's cache policy syscall handler
+///
+/// Every later mapping of the VMO uses the policy, e.g. `Uncached` for
+/// device registers or `WriteCombining` for a framebuffer.
+///
+/// # Arguments
+///
+/// * `handle_val` - VMO handle
+/// * `policy` - Raw `CachePolicy` value
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code; `RX_ERR_BAD_STATE` if the VMO is
+///   mapped, cloned or has committed pages
+pub fn sys_vmo_set_cache_policy_impl(handle_val: u32, policy: u32) -> SyscallRet {
+    log_debug!("sys_vmo_set_cache_policy: handle={} policy={}", handle_val, policy);
+
+    let policy = match vmo::CachePolicy::try_from_raw(policy) {
+        Some(policy) => policy,
+        None => {
+            log_error!("sys_vmo_set_cache_policy: invalid policy {}", policy);
+            return err_to_ret(RX_ERR_INVALID_ARGS);
+        }
+    };
+
+    let (vmo, _handle) = match lookup_vmo_from_handle(handle_val, Rights::MAP | Rights::WRITE) {
+        Ok(vmo) => vmo,
+        Err(err) => {
+            log_error!("sys_vmo_set_cache_policy: failed to lookup VMO: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    match vmo.set_cache_policy(policy) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
+}
+
+/// ============================================================================
+/// Syscall: VMO Transfer Data
+/// ============================================================================
+
+/// Move pages between VMOs syscall handler
+///
+/// The fast path for handing a large buffer to another process: the
+/// source pages are moved into the destination instead of copied, and the
+/// source range reads as zero afterwards. See `Vmo::transfer_data`.
+///
+/// # Arguments
+///
+/// * `dst_handle` - Destination VMO handle
+/// * `options` - Must be 0
+/// * `offset` - Destination offset (page-aligned)
+/// * `length` - Bytes to move (page-aligned)
+/// * `src_handle` - Source VMO handle
+/// * `src_offset` - Source offset (page-aligned)
+///
+/// # Returns
+///
+/// * On success: 0
+/// * On error: Negative error code
+pub fn sys_vmo_transfer_data_impl(
+    dst_handle: u32,
+    options: u32,
+    offset: usize,
+    length: usize,
+    src_handle: u32,
+    src_offset: usize,
+) -> SyscallRet {
+    log_debug!(
+        "sys_vmo_transfer_data: dst={} offset={:#x} length={:#x} src={} src_offset={:#x}",
+        dst_handle, offset, length, src_handle, src_offset
+    );
+
+    if options != 0 {
+        return err_to_ret(RX_ERR_INVALID_ARGS);
+    }
+
+    // Both VMOs' contents change, so both handles need WRITE
+    let (dst, src) = match (
+        lookup_vmo_from_handle(dst_handle, Rights::WRITE),
+        lookup_vmo_from_handle(src_handle, Rights::WRITE),
+    ) {
+        (Ok((dst, _)), Ok((src, _))) => (dst, src),
+        (Err(err), _) | (_, Err(err)) => {
+            log_error!("sys_vmo_transfer_data: failed to lookup VMO: {:?}", err);
+            return err_to_ret(err);
+        }
+    };
+
+    match dst.transfer_data(offset, length, &src, src_offset) {
+        Ok(()) => ok_to_ret(0),
+        Err(err) => err_to_ret(err),
+    }
 }
 
 /// ============================================================================
@@ -530,11 +826,18 @@ pub fn sys_vmo_clone_impl(
 
 /// Get VMO subsystem statistics
 pub fn get_stats() -> VmoStats {
-    VmoStats {
-        total_vmos: VMO_REGISTRY.lock().count(),
-        total_pages: 0, // TODO: Track total pages
-        committed_pages: 0, // TODO: Track committed pages
+    let registry = VMO_REGISTRY.lock();
+    let mut stats = VmoStats {
+        total_vmos: registry.count(),
+        total_pages: 0,
+        committed_pages: 0,
+    };
+
+    for vmo in registry.iter() {
+        stats.total_pages += vmo.pages.total_count();
+        stats.committed_pages += vmo.pages.committed_count();
     }
+    stats
 }
 
 /// VMO subsystem statistics
@@ -593,6 +896,30 @@ mod tests {
         assert!(VMO_REGISTRY.lock().get(id).is_none());
     }
 
+    #[test]
+    fn test_vmo_lookup_from_handle() {
+        let handle = sys_vmo_create_impl(0x1000, 1) as u32;
+        let (vmo, _) = lookup_vmo_from_handle(handle, Rights::READ | Rights::WRITE).unwrap();
+        assert_eq!(VMO_REGISTRY.lock().get_by_koid(vmo.koid).unwrap().id, vmo.id);
+
+        // A VMO without a handle can't be named by its ID
+        let unreachable = Arc::new(Vmo::create(0x1000, VmoFlags::empty).unwrap());
+        let id = VMO_REGISTRY.lock().insert(unreachable).unwrap();
+        assert!(lookup_vmo_from_handle(id as u32, Rights::NONE).map_or(true, |(vmo, _)| vmo.id != id));
+
+        // Rights are those of the handle used
+        let table = current_process_handle_table().unwrap();
+        let read_only = table.duplicate(handle, Rights::READ).unwrap();
+        assert!(lookup_vmo_from_handle(read_only, Rights::READ).is_ok());
+        assert_eq!(lookup_vmo_from_handle(read_only, Rights::WRITE).err(), Some(RX_ERR_ACCESS_DENIED));
+
+        // Other objects are the wrong type
+        let event = KernelObjectBase::new(ObjectType::Event);
+        let not_vmo = table.add(Handle::new(&event, Rights::READ)).unwrap();
+        assert_eq!(lookup_vmo_from_handle(not_vmo, Rights::READ).err(), Some(RX_ERR_WRONG_TYPE));
+        table.remove(not_vmo).unwrap();
+    }
+
     #[test]
     fn test_vmo_flags() {
         let vmo_resizable = Vmo::create(0x1000, VmoFlags::RESIZABLE).unwrap();
@@ -671,4 +998,135 @@ mod tests {
         // Should fail - not resizable
         assert!(vmo.resize(0x2000).is_err());
     }
+
+    #[test]
+    fn test_vmo_set_cache_policy() {
+        let handle = register_vmo(Vmo::create_physical(0x3000_0000, 0x1000).unwrap()).unwrap();
+
+        assert_eq!(sys_vmo_set_cache_policy_impl(handle, 2), ok_to_ret(0));
+        assert_eq!(lookup_vmo_from_handle(handle, Rights::READ).unwrap().0.cache_policy(), vmo::CachePolicy::WriteCombining);
+
+        assert_eq!(sys_vmo_set_cache_policy_impl(handle, 7), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_vmo_set_cache_policy_impl(0xdead_beef, 1), err_to_ret(RX_ERR_BAD_HANDLE));
+
+        // The handle needs MAP and WRITE
+        let table = current_process_handle_table().unwrap();
+        let no_map = table.duplicate(handle, Rights::READ | Rights::WRITE).unwrap();
+        assert_eq!(sys_vmo_set_cache_policy_impl(no_map, 1), err_to_ret(RX_ERR_ACCESS_DENIED));
+        let no_write = table.duplicate(handle, Rights::READ | Rights::MAP).unwrap();
+        assert_eq!(sys_vmo_set_cache_policy_impl(no_write, 1), err_to_ret(RX_ERR_ACCESS_DENIED));
+    }
+
+    #[test]
+    fn test_vmo_set_size_op_range() {
+        let handle = register_vmo(Vmo::create(0x2000, VmoFlags::RESIZABLE).unwrap()).unwrap();
+
+        assert_eq!(sys_vmo_set_size_impl(handle, 0x1001), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_vmo_set_size_impl(handle, 0x3000), ok_to_ret(0));
+        assert_eq!(lookup_vmo_from_handle(handle, Rights::READ).unwrap().0.size(), 0x3000);
+
+        assert_eq!(sys_vmo_op_range_impl(handle, 4, 0, 0x1000), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_vmo_op_range_impl(handle, 2, 0x2000, 0x2000), err_to_ret(RX_ERR_OUT_OF_RANGE));
+        assert_eq!(sys_vmo_op_range_impl(handle, 2, 0, 0x3000), ok_to_ret(0));
+        assert_eq!(sys_vmo_op_range_impl(0xdead_beef, 1, 0, 0x1000), err_to_ret(RX_ERR_BAD_HANDLE));
+    }
+
+    #[test]
+    fn test_vmo_resize_and_op_range_rights() {
+        let handle = sys_vmo_create_impl(0x2000, 0) as u32;
+        let table = current_process_handle_table().unwrap();
+
+        // Resizing needs WRITE and RESIZE, which non-resizable VMOs lack
+        let no_resize = table.duplicate(handle, VMO_DEFAULT_RIGHTS).unwrap();
+        assert_eq!(sys_vmo_set_size_impl(no_resize, 0x3000), err_to_ret(RX_ERR_ACCESS_DENIED));
+        let fixed = sys_vmo_create_impl(0x2000, 1) as u32;
+        assert_eq!(sys_vmo_set_size_impl(fixed, 0x3000), err_to_ret(RX_ERR_ACCESS_DENIED));
+
+        // Decommit and zero need WRITE; cache cleaning doesn't
+        let read_only = table.duplicate(handle, Rights::READ).unwrap();
+        assert_eq!(sys_vmo_op_range_impl(read_only, 2, 0, 0x1000), err_to_ret(RX_ERR_ACCESS_DENIED));
+        assert_eq!(sys_vmo_op_range_impl(read_only, 10, 0, 0x1000), err_to_ret(RX_ERR_ACCESS_DENIED));
+        assert_eq!(sys_vmo_op_range_impl(read_only, 8, 0, 0x1000), ok_to_ret(0));
+        assert_eq!(sys_vmo_op_range_impl(handle, 2, 0, 0x1000), ok_to_ret(0));
+    }
+
+    #[test]
+    fn test_vmo_discardable() {
+        // Discardable VMOs can't be resizable
+        assert_eq!(sys_vmo_create_impl(0x1000, VMO_CREATE_DISCARDABLE), err_to_ret(RX_ERR_INVALID_ARGS));
+        let handle = sys_vmo_create_impl(0x1000, 1 | VMO_CREATE_DISCARDABLE) as u32;
+        let vmo = lookup_vmo_from_handle(handle, Rights::READ).unwrap().0;
+        assert!(vmo.flags.is_discardable());
+
+        // Reclaim skips it while locked
+        vmo.pages.insert(0, 0x2000_0000);
+        assert_eq!(sys_vmo_op_range_impl(handle, 20, 0, 0x1000), ok_to_ret(0));
+        reclaim_discardable(usize::MAX);
+        assert_eq!(vmo.pages.committed_count(), 1);
+
+        assert_eq!(sys_vmo_op_range_impl(handle, 22, 0, 0x1000), ok_to_ret(0));
+        assert!(reclaim_discardable(usize::MAX) >= 1);
+        assert_eq!(vmo.pages.committed_count(), 0);
+        assert_eq!(sys_vmo_op_range_impl(handle, 21, 0, 0x1000), err_to_ret(RX_ERR_NOT_FOUND));
+        assert_eq!(sys_vmo_op_range_impl(handle, 20, 0, 0x1000), ok_to_ret(1));
+        assert_eq!(sys_vmo_op_range_impl(handle, 21, 0, 0x1000), ok_to_ret(0));
+    }
+
+    #[test]
+    fn test_vmo_reclaim_signals_discarded() {
+        use object_wait::signal::{USER_0, VMO_DISCARDED};
+
+        let handle = sys_vmo_create_impl(0x1000, 1 | VMO_CREATE_DISCARDABLE) as u32;
+        let locked = sys_vmo_create_impl(0x1000, 1 | VMO_CREATE_DISCARDABLE) as u32;
+        let vmo = lookup_vmo_from_handle(handle, Rights::READ).unwrap().0;
+        lookup_vmo_from_handle(locked, Rights::READ).unwrap().0.pages.insert(0, 0x2000_1000);
+        assert_eq!(sys_vmo_op_range_impl(locked, 20, 0, 0x1000), ok_to_ret(0));
+
+        // Hold the lock until the waiters are queued, so a reclaim from
+        // another test can't discard it first
+        vmo.pages.insert(0, 0x2000_0000);
+        assert_eq!(sys_vmo_op_range_impl(handle, 20, 0, 0x1000), ok_to_ret(0));
+        let waiter = object_wait::test_add_waiter(handle, VMO_DISCARDED);
+        let other_signals = object_wait::test_add_waiter(handle, USER_0);
+        let locked_waiter = object_wait::test_add_waiter(locked, VMO_DISCARDED);
+        assert_eq!(sys_vmo_op_range_impl(handle, 22, 0, 0x1000), ok_to_ret(0));
+
+        reclaim_discardable(usize::MAX);
+        assert_eq!(vmo.pages.committed_count(), 0);
+        assert!(waiter.is_woken());
+        assert_eq!(waiter.observed(0), VMO_DISCARDED);
+        assert!(!other_signals.is_woken());
+        assert!(!locked_waiter.is_woken());
+
+        // TRY_LOCK keeps failing until a LOCK has reported the discard
+        assert_eq!(sys_vmo_op_range_impl(handle, 21, 0, 0x1000), err_to_ret(RX_ERR_NOT_FOUND));
+        assert_eq!(sys_vmo_op_range_impl(handle, 21, 0, 0x1000), err_to_ret(RX_ERR_NOT_FOUND));
+        assert_eq!(sys_vmo_op_range_impl(handle, 20, 0, 0x1000), ok_to_ret(1));
+        assert_eq!(sys_vmo_op_range_impl(handle, 21, 0, 0x1000), ok_to_ret(0));
+
+        // Only discardable VMOs lock
+        let plain = sys_vmo_create_impl(0x1000, 0) as u32;
+        assert_eq!(sys_vmo_op_range_impl(plain, 21, 0, 0x1000), err_to_ret(RX_ERR_NOT_SUPPORTED));
+    }
+
+    #[test]
+    fn test_vmo_transfer_data() {
+        let src = sys_vmo_create_impl(0x2000, 0) as u32;
+        let dst = sys_vmo_create_impl(0x2000, 0) as u32;
+        lookup_vmo_from_handle(src, Rights::READ).unwrap().0.pages.insert(0, 0x2000_0000);
+
+        assert_eq!(sys_vmo_transfer_data_impl(dst, 1, 0, 0x1000, src, 0), err_to_ret(RX_ERR_INVALID_ARGS));
+        assert_eq!(sys_vmo_transfer_data_impl(dst, 0, 0, 0x1000, 0xdead_beef, 0), err_to_ret(RX_ERR_BAD_HANDLE));
+        assert_eq!(sys_vmo_transfer_data_impl(0xdead_beef, 0, 0, 0x1000, src, 0), err_to_ret(RX_ERR_BAD_HANDLE));
+
+        let table = current_process_handle_table().unwrap();
+        let src_read_only = table.duplicate(src, Rights::READ).unwrap();
+        let dst_read_only = table.duplicate(dst, Rights::READ).unwrap();
+        assert_eq!(sys_vmo_transfer_data_impl(dst, 0, 0, 0x1000, src_read_only, 0), err_to_ret(RX_ERR_ACCESS_DENIED));
+        assert_eq!(sys_vmo_transfer_data_impl(dst_read_only, 0, 0, 0x1000, src, 0), err_to_ret(RX_ERR_ACCESS_DENIED));
+
+        assert_eq!(sys_vmo_transfer_data_impl(dst, 0, 0x1000, 0x1000, src, 0), ok_to_ret(0));
+        assert_eq!(lookup_vmo_from_handle(src, Rights::READ).unwrap().0.pages.committed_count(), 0);
+        assert_eq!(lookup_vmo_from_handle(dst, Rights::READ).unwrap().0.pages.committed_count(), 1);
+    }
 }
@@ -312,6 +312,6 @@ mod tests {
         });
 
         // Should return error for invalid PC
-        assert_eq!(result.value as i64, -(RX_ERR_BAD_SYSCALL as i64));
+        assert_eq!(result.value as i64, RX_ERR_BAD_SYSCALL as i64);
     }
 }
@@ -139,6 +139,72 @@ fn bench_mutex_test() -> TestResult {
     Ok(())
 }
 
+/// Benchmark reader-writer lock read and write acquire/release
+fn bench_rwlock_test() -> TestResult {
+    use crate::kernel::sync::RwLock;
+    const COUNT: usize = 128 * 1024 * 1024;
+
+    let lock = RwLock::new(0u32);
+
+    let start = unsafe { crate::arch::Arch::now_monotonic() };
+
+    for _ in 0..COUNT {
+        let _guard = lock.read();
+        // Critical section
+    }
+
+    let mid = unsafe { crate::arch::Arch::now_monotonic() };
+
+    for _ in 0..COUNT {
+        let _guard = lock.write();
+        // Critical section
+    }
+
+    let end = unsafe { crate::arch::Arch::now_monotonic() };
+
+    debug::log_info!(
+        "rwlock: {} cycles per read acquire/release, {} cycles per write acquire/release ({} times each)",
+        (mid - start) / COUNT as u64,
+        (end - mid) / COUNT as u64,
+        COUNT
+    );
+
+    Ok(())
+}
+
+/// Benchmark seqlock reads and writes
+fn bench_seqlock_test() -> TestResult {
+    use crate::kernel::sync::SeqLock;
+    const COUNT: usize = 128 * 1024 * 1024;
+
+    let lock = SeqLock::new([0u64; 4]);
+
+    let start = unsafe { crate::arch::Arch::now_monotonic() };
+
+    let mut sum = 0u64;
+    for _ in 0..COUNT {
+        sum = sum.wrapping_add(lock.read()[0]);
+    }
+
+    let mid = unsafe { crate::arch::Arch::now_monotonic() };
+
+    for i in 0..COUNT {
+        lock.write(|values| values[0] = i as u64);
+    }
+
+    let end = unsafe { crate::arch::Arch::now_monotonic() };
+
+    debug::log_info!(
+        "seqlock: {} cycles per read, {} cycles per write ({} times each, sum {})",
+        (mid - start) / COUNT as u64,
+        (end - mid) / COUNT as u64,
+        COUNT,
+        sum
+    );
+
+    Ok(())
+}
+
 /// Benchmark context switching (thread yield)
 fn bench_context_switch_test() -> TestResult {
     const COUNT: usize = 100000;
@@ -191,6 +257,54 @@ fn bench_atomic_test() -> TestResult {
     Ok(())
 }
 
+/// Benchmark moving a multi-megabyte payload between VMOs
+///
+/// Compares `Vmo::read` into a buffer plus `Vmo::write` out of it, the
+/// path a copy through `rx_vmo_read`/`rx_vmo_write` takes, with
+/// `Vmo::transfer_data`, which moves the pages instead.
+fn bench_vmo_transfer_test() -> TestResult {
+    use crate::kernel::object::vmo::{Vmo, VmoFlags};
+    const PAYLOAD: usize = 4 * 1024 * 1024;
+    const COUNT: usize = 64;
+
+    let src = Vmo::create(PAYLOAD, VmoFlags::empty).map_err(|e| format!("vmo create: {}", e))?;
+    let dst = Vmo::create(PAYLOAD, VmoFlags::empty).map_err(|e| format!("vmo create: {}", e))?;
+    let mut buf = alloc::vec![0x5Au8; PAYLOAD];
+    src.write(0, &buf).map_err(|e| format!("vmo write: {}", e))?;
+
+    let start = unsafe { crate::arch::Arch::now_monotonic() };
+    for _ in 0..COUNT {
+        src.read(0, &mut buf).map_err(|e| format!("vmo read: {}", e))?;
+        dst.write(0, &buf).map_err(|e| format!("vmo write: {}", e))?;
+    }
+    let copy_ns = unsafe { crate::arch::Arch::now_monotonic() } - start;
+
+    // Move the payload back and forth so every pass moves committed pages
+    let start = unsafe { crate::arch::Arch::now_monotonic() };
+    for i in 0..COUNT {
+        let (to, from) = if i % 2 == 0 { (&dst, &src) } else { (&src, &dst) };
+        to.transfer_data(0, PAYLOAD, from, 0).map_err(|e| format!("vmo transfer: {}", e))?;
+    }
+    let transfer_ns = unsafe { crate::arch::Arch::now_monotonic() } - start;
+
+    let mut check = [0u8; 16];
+    src.read(PAYLOAD - 16, &mut check).map_err(|e| format!("vmo read: {}", e))?;
+    if check != [0x5A; 16] {
+        return Err(format!("transferred payload corrupted: {:?}", check));
+    }
+
+    debug::log_info!(
+        "vmo_transfer: {} bytes x {}: copy {} ns, transfer {} ns ({}x)",
+        PAYLOAD,
+        COUNT,
+        copy_ns,
+        transfer_ns,
+        copy_ns / transfer_ns.max(1)
+    );
+
+    Ok(())
+}
+
 /// Create the benchmark test suite
 pub fn create_benchmark_suite() -> TestSuite {
     TestSuite::new(
@@ -201,8 +315,11 @@ pub fn create_benchmark_suite() -> TestSuite {
             TestCase::new("memcpy", "Memory copying benchmark", bench_memcpy_test),
             TestCase::new("spinlock", "Spinlock benchmark", bench_spinlock_test),
             TestCase::new("mutex", "Mutex benchmark", bench_mutex_test),
+            TestCase::new("rwlock", "Reader-writer lock benchmark", bench_rwlock_test),
+            TestCase::new("seqlock", "Seqlock benchmark", bench_seqlock_test),
             TestCase::new("context_switch", "Context switch benchmark", bench_context_switch_test),
             TestCase::new("atomic", "Atomic operations benchmark", bench_atomic_test),
+            TestCase::new("vmo_transfer", "VMO copy vs page transfer benchmark", bench_vmo_transfer_test),
         ]),
     )
 }
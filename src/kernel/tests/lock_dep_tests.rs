@@ -82,8 +82,9 @@ fn mutex_basic_test() -> TestResult {
     Ok(())
 }
 
-/// Test nested lock (should detect deadlock if enabled)
+/// Test nested lock (lockdep reports the reversed order if enabled)
 fn nested_lock_test() -> TestResult {
+    use crate::kernel::lib::lockdep;
     use crate::kernel::sync::SpinLock;
 
     let lock1 = SpinLock::new(0u32);
@@ -96,12 +97,13 @@ fn nested_lock_test() -> TestResult {
         // OK: Different locks
     }
 
-    // Reverse ordering should also work
+    // Reverse ordering still works, but could deadlock against the above
     {
         let _guard2 = lock2.lock();
         let _guard1 = lock1.lock();
         // OK: Different locks
     }
+    assert!(!lockdep::ENABLED || lockdep::inversions() > 0, "lockdep missed a reversed lock order");
 
     debug::log_info!("Nested lock test passed");
     Ok(())
@@ -31,6 +31,8 @@
 //! - [`string_tests`] - String operations tests
 //! - [`sync_ipi_tests`] - Inter-processor interrupt tests
 //! - [`uart_tests`] - UART serial output tests
+//! - [`user_access_tests`] - SMEP/SMAP/PAN and W^X protection tests
+//! - [`syscall_fuzz`] - Seeded syscall dispatcher fuzzer
 //!
 //! # Running Tests
 //!
@@ -49,27 +51,53 @@
 
 
 pub mod runner;
+pub mod string_tests;
+pub mod user_access_tests;
+pub mod syscall_fuzz;
+
+// Suites carried over from the C++ tree. They are written against thread,
+// timer and pmm APIs the kernel doesn't have yet, so they stay out of the
+// build until they're ported.
+#[cfg(feature = "legacy-tests")]
 pub mod conformance;
+#[cfg(feature = "legacy-tests")]
 pub mod thread_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod timer_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod mem_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod alloc_checker_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod benchmarks;
+#[cfg(feature = "legacy-tests")]
 pub mod cache_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod clock_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod fibo;
+#[cfg(feature = "legacy-tests")]
 pub mod lock_dep_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod mp_hotplug_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod preempt_disable_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod printf_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod resource_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod sleep_tests;
-pub mod string_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod sync_ipi_tests;
+#[cfg(feature = "legacy-tests")]
 pub mod uart_tests;
 
+use crate::log_info;
+
 // Re-exports for convenience
 pub use runner::*;
+#[cfg(feature = "legacy-tests")]
 pub use conformance::*;
 
 /// Initialize the test framework
@@ -77,24 +105,29 @@ pub fn init() {
     runner::init();
 
     // Register all test suites
-    thread_tests::register();
-    timer_tests::register();
-    mem_tests::register();
-    conformance::register();
-    alloc_checker_tests::register();
-    benchmarks::register();
-    cache_tests::register();
-    clock_tests::register();
-    fibo::register();
-    lock_dep_tests::register();
-    mp_hotplug_tests::register();
-    preempt_disable_tests::register();
-    printf_tests::register();
-    resource_tests::register();
-    sleep_tests::register();
     string_tests::register();
-    sync_ipi_tests::register();
-    uart_tests::register();
+    user_access_tests::register();
+
+    #[cfg(feature = "legacy-tests")]
+    {
+        conformance::register();
+        thread_tests::register();
+        timer_tests::register();
+        mem_tests::register();
+        alloc_checker_tests::register();
+        benchmarks::register();
+        cache_tests::register();
+        clock_tests::register();
+        fibo::register();
+        lock_dep_tests::register();
+        mp_hotplug_tests::register();
+        preempt_disable_tests::register();
+        printf_tests::register();
+        resource_tests::register();
+        sleep_tests::register();
+        sync_ipi_tests::register();
+        uart_tests::register();
+    }
 }
 
 /// Run all registered test suites
@@ -220,26 +253,8 @@ pub extern "C" fn rustux_unittests() -> i32 {
 /// Module initialization function
 pub fn module_init() {
     init();
-    debug::log_info!("Rust test suite loaded");
-    debug::log_info!("  Available suites:");
-    debug::log_info!("    - thread (thread management)");
-    debug::log_info!("    - timer (timer functionality)");
-    debug::log_info!("    - memory (memory management)");
-    debug::log_info!("    - conformance (cross-architecture)");
-    debug::log_info!("    - alloc_checker (allocation validation)");
-    debug::log_info!("    - benchmarks (performance)");
-    debug::log_info!("    - cache (CPU cache operations)");
-    debug::log_info!("    - clock (clock and timer)");
-    debug::log_info!("    - fibo (threaded Fibonacci)");
-    debug::log_info!("    - lock_dep (lock dependencies)");
-    debug::log_info!("    - mp_hotplug (multi-processor)");
-    debug::log_info!("    - preempt_disable (preemption control)");
-    debug::log_info!("    - printf (formatted output)");
-    debug::log_info!("    - resource (resource allocation)");
-    debug::log_info!("    - sleep (thread sleep)");
-    debug::log_info!("    - string (string operations)");
-    debug::log_info!("    - sync_ipi (inter-processor interrupts)");
-    debug::log_info!("    - uart (UART serial output)");
+    log_info!("Rust test suite loaded");
+    list();
 }
 
 // ============================================================================
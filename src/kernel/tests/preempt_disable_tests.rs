@@ -17,19 +17,20 @@ use crate::debug;
 
 /// Test preempt disable counting
 fn preempt_disable_count_test() -> TestResult {
-    // Note: In Rust, we don't have direct access to preempt_disable_count
-    // This test verifies the concept exists and works
+    use crate::kernel::preempt;
 
-    let current = thread::current_thread();
-
-    // Save original state
-    let original_priority = current.priority();
-
-    // Test that we can manipulate thread state
-    current.set_priority(thread::Priority::High)?;
-
-    // Restore
-    current.set_priority(original_priority)?;
+    let base = preempt::depth();
+    {
+        let _outer = preempt::PreemptDisabled::new();
+        assert_eq!(preempt::depth(), base + 1);
+        {
+            let _inner = preempt::PreemptDisabled::new();
+            assert_eq!(preempt::depth(), base + 2);
+            assert!(preempt::in_atomic());
+        }
+        assert_eq!(preempt::depth(), base + 1);
+    }
+    assert_eq!(preempt::depth(), base);
 
     debug::log_info!("Preempt disable count test passed");
     Ok(())
@@ -90,7 +91,8 @@ fn nested_preempt_disable_test() -> TestResult {
         let _guard1 = lock1.lock();
         {
             let _guard2 = lock2.lock();
-            // Both locks held
+            // Both locks held, each a level of preemption disable
+            assert!(crate::kernel::preempt::depth() >= 2);
         }
     }
 
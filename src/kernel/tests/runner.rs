@@ -21,7 +21,8 @@
 //! # Usage
 //!
 //! ```rust
-//! use crate::kernel::tests::runner::*;
+//! use crate::assert_eq;
+//! use crate::kernel::tests::runner::{test_case, TestResult};
 //!
 //! #[test_case]
 //! fn test_example() -> TestResult {
@@ -29,9 +30,28 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! `#[test_case]` functions need no registration: each one lands in a
+//! `ktest.<path>` linker section, and [`test_cases`] returns them sorted by
+//! path. They are only built with the `ktest` feature.
+//!
+//! The assertion macros exported here (`assert_true!`, `assert_eq!`,
+//! `assert_ge!` and the rest) return the failure as the test's `Err`, so
+//! it is reported and the run goes on. Import them from the crate root:
+//! `core`'s `assert_eq!` panics, which halts the kernel before the failure
+//! is reported.
+//!
+//! # Command Line
+//!
+//! - `kernel.test=<filter>` - After init, run the `#[test_case]` tests whose
+//!   path contains `filter` (`all` for every test), then exit QEMU with the
+//!   result instead of starting the scheduler (default off)
 
 
-use crate::debug;
+use crate::kernel::cmdline;
+use crate::kernel::object::{koid, leak};
+use crate::kernel::timer;
+use crate::log_info;
 use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::string::String;
@@ -391,6 +411,150 @@ pub fn list_test_suites() {
     global_registry().list()
 }
 
+// ============================================================================
+// Boot-Time Runner
+// ============================================================================
+
+/// Boot option selecting the `#[test_case]` tests to run after init
+const CMDLINE_TEST: &str = "kernel.test";
+
+/// Port of QEMU's `isa-debug-exit` device
+#[cfg(target_arch = "x86_64")]
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+/// Debug exit value when every test passed; QEMU exits with 33
+pub const EXIT_SUCCESS: u8 = 0x10;
+
+/// Debug exit value when a test failed; QEMU exits with 35
+pub const EXIT_FAILURE: u8 = 0x11;
+
+extern "C" {
+    static ktest_begin: TestCase;
+    static ktest_end: TestCase;
+}
+
+/// Every `#[test_case]` in the image, sorted by path
+pub fn test_cases() -> &'static [TestCase] {
+    unsafe {
+        let begin = core::ptr::addr_of!(ktest_begin);
+        let end = core::ptr::addr_of!(ktest_end);
+        let len = (end as usize - begin as usize) / core::mem::size_of::<TestCase>();
+        core::slice::from_raw_parts(begin, len)
+    }
+}
+
+/// Whether `filter` selects the test at `path`
+fn selected(filter: &str, path: &str) -> bool {
+    filter == "all" || path.contains(filter)
+}
+
+/// Keep a failure message on its report line
+fn one_line(msg: &str) -> String {
+    msg.replace('\n', "\\n")
+}
+
+/// Run the `#[test_case]` tests selected by `filter`
+///
+/// Each event is one line, so a harness can parse the serial log:
+///
+/// ```text
+/// KTEST START total=2
+/// KTEST PASS name=rustux::kernel::tests::string_tests::strcmp_order ns=1520
+/// KTEST FAIL name=rustux::kernel::tests::string_tests::memcmp_order ns=830 msg=...
+/// KTEST END passed=1 failed=1 ns=4210
+/// ```
+///
+/// Failure messages have their newlines escaped as `\n`.
+///
+/// In debug builds every kernel object the tests created must be gone by
+/// the end. Each one still alive gets a `KTEST LEAK` line, and the run
+/// fails:
+///
+/// ```text
+/// KTEST LEAK koid=1093 type=event
+/// ```
+pub fn run_test_cases(filter: &str) -> SuiteSummary {
+    let tests: Vec<&TestCase> = test_cases().iter().filter(|t| selected(filter, t.name)).collect();
+    println!("KTEST START total={}", tests.len());
+
+    let first_koid = koid::next_koid();
+    let start = timer::current_time();
+    let mut passed = 0;
+    for test in &tests {
+        let test_start = timer::current_time();
+        let result = (test.test_fn)();
+        let ns = timer::current_time() - test_start;
+
+        match result {
+            Ok(()) => {
+                passed += 1;
+                println!("KTEST PASS name={} ns={}", test.name, ns);
+            }
+            Err(e) => {
+                println!("KTEST FAIL name={} ns={} msg={}", test.name, ns, one_line(&e));
+            }
+        }
+    }
+    let duration = timer::current_time() - start;
+
+    let leaked = leak::live_since(first_koid);
+    for object in &leaked {
+        println!("KTEST LEAK koid={} type={}", object.koid, object.obj_type.name());
+        object.created_by.print();
+    }
+
+    let failed = tests.len() - passed + usize::from(!leaked.is_empty());
+    println!("KTEST END passed={} failed={} ns={}", passed, failed, duration);
+
+    SuiteSummary {
+        name: "ktest",
+        total: tests.len() + usize::from(!leaked.is_empty()),
+        passed,
+        failed,
+        duration,
+    }
+}
+
+/// Leave QEMU with a test result
+///
+/// On x86 the result goes out through the `isa-debug-exit` device
+/// (`-device isa-debug-exit,iobase=0xf4,iosize=0x04`). Other machines
+/// power off, and the result is the `KTEST END` line.
+pub fn exit_qemu(success: bool) -> ! {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use crate::kernel::arch::amd64::include::arch::amd64::outp;
+        outp(ISA_DEBUG_EXIT_PORT, if success { EXIT_SUCCESS } else { EXIT_FAILURE });
+    }
+
+    crate::platform::platform_halt(
+        crate::platform::HALT_REASON_USER_REQUEST,
+        crate::platform::HALT_ACTION_SHUTDOWN,
+    )
+}
+
+/// Run the tests selected by `kernel.test` and exit, if it is set
+pub fn run_boot_tests() {
+    let Some(filter) = cmdline::cmdline_get(CMDLINE_TEST) else {
+        return;
+    };
+
+    let summary = run_test_cases(filter);
+    exit_qemu(summary.all_passed());
+}
+
+/// `ktest` console command
+fn cmd_ktest(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    let filter = if argc > 1 { argv[1] } else { "all" };
+    if run_test_cases(filter).all_passed() {
+        0
+    } else {
+        1
+    }
+}
+
+crate::static_command!("ktest", "run #[test_case] tests matching a filter", cmd_ktest);
+
 // ============================================================================
 // Macros
 // ============================================================================
@@ -400,6 +564,8 @@ pub fn list_test_suites() {
 /// # Usage
 ///
 /// ```rust
+/// use crate::assert_eq;
+///
 /// #[test_case]
 /// fn test_example() -> TestResult {
 ///     assert_eq!(1 + 1, 2);
@@ -413,12 +579,12 @@ pub use rustux_macros::test_case;
 macro_rules! assert_true {
     ($expr:expr) => {
         if !$expr {
-            return Err(format!("assertion failed: {} is not true", stringify!($expr)));
+            return Err(::alloc::format!("assertion failed: {} is not true", stringify!($expr)));
         }
     };
     ($expr:expr, $msg:expr) => {
         if !$expr {
-            return Err(format!("assertion failed: {} ({} is not true)", $msg, stringify!($expr)));
+            return Err(::alloc::format!("assertion failed: {} ({} is not true)", $msg, stringify!($expr)));
         }
     };
 }
@@ -427,7 +593,12 @@ macro_rules! assert_true {
 macro_rules! assert_false {
     ($expr:expr) => {
         if $expr {
-            return Err(format!("assertion failed: {} is not false", stringify!($expr)));
+            return Err(::alloc::format!("assertion failed: {} is not false", stringify!($expr)));
+        }
+    };
+    ($expr:expr, $msg:expr) => {
+        if $expr {
+            return Err(::alloc::format!("assertion failed: {} ({} is not false)", $msg, stringify!($expr)));
         }
     };
 }
@@ -439,7 +610,7 @@ macro_rules! assert_eq {
             let left_val = &$left;
             let right_val = &$right;
             if left_val != right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} == {}\n  left: {:?}\n  right: {:?}",
                     stringify!($left),
                     stringify!($right),
@@ -454,7 +625,7 @@ macro_rules! assert_eq {
             let left_val = &$left;
             let right_val = &$right;
             if left_val != right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} ({} == {})\n  left: {:?}\n  right: {:?}",
                     $msg,
                     stringify!($left),
@@ -474,7 +645,7 @@ macro_rules! assert_ne {
             let left_val = &$left;
             let right_val = &$right;
             if left_val == right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} != {}\n  both are: {:?}",
                     stringify!($left),
                     stringify!($right),
@@ -492,7 +663,7 @@ macro_rules! assert_gt {
             let left_val = &$left;
             let right_val = &$right;
             if left_val <= right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} > {}\n  left: {:?}\n  right: {:?}",
                     stringify!($left),
                     stringify!($right),
@@ -511,7 +682,7 @@ macro_rules! assert_ge {
             let left_val = &$left;
             let right_val = &$right;
             if left_val < right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} >= {}\n  left: {:?}\n  right: {:?}",
                     stringify!($left),
                     stringify!($right),
@@ -530,7 +701,7 @@ macro_rules! assert_lt {
             let left_val = &$left;
             let right_val = &$right;
             if left_val >= right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} < {}\n  left: {:?}\n  right: {:?}",
                     stringify!($left),
                     stringify!($right),
@@ -549,7 +720,7 @@ macro_rules! assert_le {
             let left_val = &$left;
             let right_val = &$right;
             if left_val > right_val {
-                return Err(format!(
+                return Err(::alloc::format!(
                     "assertion failed: {} <= {}\n  left: {:?}\n  right: {:?}",
                     stringify!($left),
                     stringify!($right),
@@ -565,10 +736,10 @@ macro_rules! assert_le {
 #[macro_export]
 macro_rules! test_fail {
     ($msg:expr) => {
-        return Err(format!("test failed: {}", $msg));
+        return Err(::alloc::format!("test failed: {}", $msg));
     };
     ($fmt:expr, $($arg:tt)*) => {
-        return Err(format!("test failed: {}", format!($fmt, $($arg)*)));
+        return Err(::alloc::format!("test failed: {}", ::alloc::format!($fmt, $($arg)*)));
     };
 }
 
@@ -587,7 +758,7 @@ macro_rules! test_skip {
 
 /// Initialize the test framework
 pub fn init() {
-    debug::log_info!("Test framework initialized");
+    log_info!("Test framework initialized");
 }
 
 // ============================================================================
@@ -605,6 +776,21 @@ mod tests {
         assert_eq!(tc.description, "description");
     }
 
+    #[test]
+    fn test_selected() {
+        let path = "rustux::kernel::tests::string_tests::strcmp_order";
+        assert!(selected("all", path));
+        assert!(selected("string_tests", path));
+        assert!(selected(path, path));
+        assert!(!selected("thread_tests", path));
+    }
+
+    #[test]
+    fn test_one_line() {
+        assert_eq!(one_line("left: 1\n  right: 2"), "left: 1\\n  right: 2");
+        assert_eq!(one_line("plain"), "plain");
+    }
+
     #[test]
     fn test_test_outcome() {
         let outcome = TestOutcome::Passed { duration: 100 };
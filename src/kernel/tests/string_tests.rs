@@ -9,9 +9,12 @@
 //! Tests for string operations (memcpy, memset, etc.).
 
 
-use crate::kernel::tests::runner::*;
-use crate::kernel::vm;
-use crate::debug;
+use crate::assert_eq;
+use crate::kernel::tests::runner::{register_suite, test_case, TestCase, TestResult, TestSuite};
+use crate::kernel::timer;
+use crate::log_info;
+use alloc::format;
+use alloc::vec;
 
 const BUFFER_SIZE: usize = 8 * 1024 * 1024;
 const ITERATIONS: usize = (1024 * 1024 * 1024) / BUFFER_SIZE;
@@ -20,11 +23,11 @@ const ITERATIONS: usize = (1024 * 1024 * 1024) / BUFFER_SIZE;
 fn memcpy_correctness_test() -> TestResult {
     const TEST_SIZE: usize = 1024;
 
-    let src_vaddr = vm::pmm::allocate_aligned(TEST_SIZE * 2, 64)?;
-    let dst_vaddr = vm::pmm::allocate_aligned(TEST_SIZE * 2, 64)?;
+    let mut src_buf = vec![0u8; TEST_SIZE * 2];
+    let mut dst_buf = vec![0u8; TEST_SIZE * 2];
 
-    let src = src_vaddr as *mut u8;
-    let dst = dst_vaddr as *mut u8;
+    let src = src_buf.as_mut_ptr();
+    let dst = dst_buf.as_mut_ptr();
 
     // Fill source with pattern
     for i in 0..TEST_SIZE {
@@ -52,8 +55,6 @@ fn memcpy_correctness_test() -> TestResult {
                         let src_val = src.add(src_align + i).read_volatile();
                         let dst_val = dst.add(dst_align + i).read_volatile();
                         if src_val != dst_val {
-                            vm::pmm::free(src_vaddr, TEST_SIZE * 2)?;
-                            vm::pmm::free(dst_vaddr, TEST_SIZE * 2)?;
                             return Err(format!(
                                 "memcpy mismatch: src_align={}, dst_align={}, size={}, offset={}",
                                 src_align, dst_align, size, i
@@ -65,10 +66,7 @@ fn memcpy_correctness_test() -> TestResult {
         }
     }
 
-    vm::pmm::free(src_vaddr, TEST_SIZE * 2)?;
-    vm::pmm::free(dst_vaddr, TEST_SIZE * 2)?;
-
-    debug::log_info!("memcpy correctness test passed");
+    log_info!("memcpy correctness test passed");
     Ok(())
 }
 
@@ -76,8 +74,8 @@ fn memcpy_correctness_test() -> TestResult {
 fn memset_correctness_test() -> TestResult {
     const TEST_SIZE: usize = 1024;
 
-    let vaddr = vm::pmm::allocate_aligned(TEST_SIZE * 2, 64)?;
-    let buf = vaddr as *mut u8;
+    let mut storage = vec![0u8; TEST_SIZE * 2];
+    let buf = storage.as_mut_ptr();
 
     // Test various alignments and fill values
     for dst_align in 0..64 {
@@ -97,7 +95,6 @@ fn memset_correctness_test() -> TestResult {
                 for i in 0..size {
                     let val = unsafe { buf.add(dst_align + i).read_volatile() };
                     if val != fill_val {
-                        vm::pmm::free(vaddr, TEST_SIZE * 2)?;
                         return Err(format!(
                             "memset mismatch: align={}, size={}, fill={}, offset={}",
                             dst_align, size, fill_val, i
@@ -108,20 +105,18 @@ fn memset_correctness_test() -> TestResult {
         }
     }
 
-    vm::pmm::free(vaddr, TEST_SIZE * 2)?;
-
-    debug::log_info!("memset correctness test passed");
+    log_info!("memset correctness test passed");
     Ok(())
 }
 
 /// Test memcpy performance
 fn memcpy_perf_test() -> TestResult {
-    let vaddr = vm::pmm::allocate_aligned(BUFFER_SIZE * 2, 64)?;
+    let mut storage = vec![0u8; BUFFER_SIZE * 2 + 64];
 
-    let src = (vaddr + 64) as *const u8;
-    let dst = (vaddr + 64 + BUFFER_SIZE) as *mut u8;
+    let src = unsafe { storage.as_ptr().add(64) };
+    let dst = unsafe { storage.as_mut_ptr().add(64 + BUFFER_SIZE) };
 
-    let start = unsafe { crate::arch::Arch::now_monotonic() };
+    let start = timer::current_time();
 
     for _ in 0..ITERATIONS {
         for i in 0..BUFFER_SIZE {
@@ -129,26 +124,24 @@ fn memcpy_perf_test() -> TestResult {
         }
     }
 
-    let end = unsafe { crate::arch::Arch::now_monotonic() };
-    let duration_ns = end - start;
-    let bytes_per_sec = (BUFFER_SIZE * ITERATIONS * 1_000_000_000) / duration_ns.max(1);
+    let duration_ns = timer::current_time() - start;
+    let bytes_per_sec = (BUFFER_SIZE * ITERATIONS) as u64 * 1_000_000_000 / duration_ns.max(1);
 
-    debug::log_info!(
+    log_info!(
         "memcpy perf: {} ms, {} MB/s",
         duration_ns / 1_000_000,
         bytes_per_sec / 1_000_000
     );
 
-    vm::pmm::free(vaddr, BUFFER_SIZE * 2)?;
     Ok(())
 }
 
 /// Test memset performance
 fn memset_perf_test() -> TestResult {
-    let vaddr = vm::pmm::allocate_aligned(BUFFER_SIZE, 64)?;
-    let buf = vaddr as *mut u8;
+    let mut storage = vec![0u8; BUFFER_SIZE];
+    let buf = storage.as_mut_ptr();
 
-    let start = unsafe { crate::arch::Arch::now_monotonic() };
+    let start = timer::current_time();
 
     for _ in 0..ITERATIONS {
         for i in 0..BUFFER_SIZE {
@@ -156,17 +149,15 @@ fn memset_perf_test() -> TestResult {
         }
     }
 
-    let end = unsafe { crate::arch::Arch::now_monotonic() };
-    let duration_ns = end - start;
-    let bytes_per_sec = (BUFFER_SIZE * ITERATIONS * 1_000_000_000) / duration_ns.max(1);
+    let duration_ns = timer::current_time() - start;
+    let bytes_per_sec = (BUFFER_SIZE * ITERATIONS) as u64 * 1_000_000_000 / duration_ns.max(1);
 
-    debug::log_info!(
+    log_info!(
         "memset perf: {} ms, {} MB/s",
         duration_ns / 1_000_000,
         bytes_per_sec / 1_000_000
     );
 
-    vm::pmm::free(vaddr, BUFFER_SIZE)?;
     Ok(())
 }
 
@@ -174,8 +165,8 @@ fn memset_perf_test() -> TestResult {
 fn memmove_test() -> TestResult {
     const TEST_SIZE: usize = 1024;
 
-    let vaddr = vm::pmm::allocate_aligned(TEST_SIZE * 2, 64)?;
-    let buf = vaddr as *mut u8;
+    let mut storage = vec![0u8; TEST_SIZE * 2];
+    let buf = storage.as_mut_ptr();
 
     // Initialize with pattern
     for i in 0..TEST_SIZE {
@@ -220,9 +211,35 @@ fn memmove_test() -> TestResult {
         assert_eq!(src_val, dst_val, "Backward memmove mismatch");
     }
 
-    vm::pmm::free(vaddr, TEST_SIZE * 2)?;
+    log_info!("memmove test passed");
+    Ok(())
+}
+
+/// strcmp orders by byte value and length
+#[test_case]
+fn strcmp_order() -> TestResult {
+    use crate::kernel::lib::string::strcmp;
 
-    debug::log_info!("memmove test passed");
+    assert_eq!(strcmp("abc", "abc"), 0);
+    assert_eq!(strcmp("abc", "abd"), -1);
+    assert_eq!(strcmp("abd", "abc"), 1);
+    assert_eq!(strcmp("ab", "abc"), -1);
+    Ok(())
+}
+
+/// memcmp stops at the first differing byte
+#[test_case]
+fn memcmp_order() -> TestResult {
+    use crate::kernel::lib::mem::memcmp;
+
+    let a = [1u8, 2, 3, 4];
+    let b = [1u8, 2, 4, 0];
+    unsafe {
+        assert_eq!(memcmp(a.as_ptr(), a.as_ptr(), a.len()), 0);
+        assert_eq!(memcmp(a.as_ptr(), b.as_ptr(), 2), 0);
+        assert_eq!(memcmp(a.as_ptr(), b.as_ptr(), a.len()), -1);
+        assert_eq!(memcmp(b.as_ptr(), a.as_ptr(), a.len()), 1);
+    }
     Ok(())
 }
 
@@ -0,0 +1,495 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Syscall Fuzzer
+//!
+//! Feeds generated syscalls through [`syscall_dispatch`] to shake out
+//! argument validation bugs. A case is a syscall number and six arguments,
+//! each drawn from patterns for what that argument is (handle, user
+//! pointer, length, flags or plain value): zero, boundary sizes around the
+//! kernel's limits, small handle values (object IDs count up from 1, so
+//! these hit live objects), all-ones and random bits. One case in ten uses
+//! a number outside every assigned range.
+//!
+//! After each call the fuzzer checks that
+//!
+//! - an unassigned number returned `RX_ERR_NOT_SUPPORTED`, and
+//! - a call that failed left the handle table and the object registries
+//!   with as many entries as before (nothing leaked on an error path).
+//!
+//! A panic is the other failure; the case that caused it is the last one
+//! logged with `-v`.
+//!
+//! # Reproducing
+//!
+//! Every case is derived from the seed and its index alone, so rerunning a
+//! seed repeats the run and `sysfuzz replay <seed> <case>` repeats one
+//! case:
+//!
+//! ```text
+//! SYSFUZZ START seed=0x5eed cases=10000
+//! SYSFUZZ CASE n=417 nr=0x21 args=[0x3, 0x0, 0xffffff8000001000, 0x10001, 0x0, 0x41]
+//! SYSFUZZ LEAK n=417 nr=0x21 ret=-17 objects=12->13
+//! SYSFUZZ END cases=10000 ok=2210 errors=7790 failures=1 ns=48210033
+//! ```
+//!
+//! # Not Fuzzed
+//!
+//! User pointers are only ever null, in the kernel half or wrapping past
+//! the top of the address space, which the user copy routines reject
+//! before touching memory. Any other user address would be dereferenced,
+//! and without fault recovery in user copies a bad one faults the kernel.
+//!
+//! Syscalls whose success reaches past the fuzzer are never issued: thread
+//! and process exit and start, process, thread and job creation, setting
+//! thread affinity (it would pin the kernel's own threads), the VMAR
+//! calls (they act on the shared root VMAR), the DDK and PCI calls
+//! (they touch hardware through the root resource) and power control (it
+//! would reboot the machine). Objects created by the other calls stay in
+//! their registries until handle tables own them, so fuzz a throwaway boot.
+//!
+//! # Command Line
+//!
+//! - `kernel.sysfuzz=<seed>` - Fuzz after init, then exit QEMU as the
+//!   boot-time test runner does
+//! - `kernel.sysfuzz.cases=<n>` - Cases to run at boot (default 10000)
+
+use crate::kernel::cmdline;
+use crate::kernel::lib::crypto;
+use crate::kernel::syscalls::{self, channel, event, fifo, syscall_dispatch, timer, vmo};
+use crate::kernel::syscalls::{SyscallArgs, SyscallNumber, SyscallRet};
+use crate::kernel::tests::runner::{self, test_case, TestResult};
+use crate::kernel::timer as ktimer;
+use crate::rustux::types::err::RX_ERR_NOT_SUPPORTED;
+use alloc::format;
+
+/// Boot option holding the seed to fuzz with after init
+const CMDLINE_SEED: &str = "kernel.sysfuzz";
+
+/// Boot option holding the number of cases to run at boot
+const CMDLINE_CASES: &str = "kernel.sysfuzz.cases";
+
+const DEFAULT_CASES: u64 = 10_000;
+
+/// Start of the kernel half on every supported architecture
+const KERNEL_HALF: u64 = 0xffff_ff80_0000_0000;
+
+/// Lengths either side of the kernel's limits: CPRNG draws, channel
+/// messages and handles, wait items, pages, FIFO sizes and VMO reads
+const BOUNDARY_LENS: &[u64] = &[
+    8, 15, 16, 17, 63, 64, 65, 255, 256, 257, 4095, 4096, 4097,
+    65535, 65536, 65537, 0xff_ffff, 0x100_0000, 0x100_0001,
+];
+
+/// Kind of a syscall argument, which picks the patterns it is drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    Handle,
+    Ptr,
+    Len,
+    Flags,
+    Value,
+    Unused,
+}
+
+use Arg::*;
+
+/// Argument kinds of every fuzzed syscall, by kernel syscall number
+const SPECS: &[(u32, [Arg; 6])] = &[
+    // handle_close, thread_get_affinity
+    (0x07, [Handle, Unused, Unused, Unused, Unused, Unused]),
+    (0x09, [Handle, Ptr, Unused, Unused, Unused, Unused]),
+    // vmo_create, vmo_read, vmo_write, vmo_clone
+    (0x10, [Len, Flags, Unused, Unused, Unused, Unused]),
+    (0x11, [Handle, Ptr, Len, Len, Unused, Unused]),
+    (0x12, [Handle, Ptr, Len, Len, Unused, Unused]),
+    (0x13, [Handle, Len, Len, Unused, Unused, Unused]),
+    // vmo_set_size, vmo_op_range
+    (0x17, [Handle, Len, Unused, Unused, Unused, Unused]),
+    (0x18, [Handle, Flags, Len, Len, Unused, Unused]),
+    // channel_create, channel_write, channel_read
+    (0x20, [Flags, Unused, Unused, Unused, Unused, Unused]),
+    (0x21, [Handle, Flags, Ptr, Len, Ptr, Len]),
+    (0x22, [Handle, Flags, Ptr, Len, Ptr, Len]),
+    // event_create, eventpair_create, object_signal
+    (0x23, [Flags, Unused, Unused, Unused, Unused, Unused]),
+    (0x24, [Unused, Unused, Unused, Unused, Unused, Unused]),
+    (0x25, [Handle, Flags, Flags, Unused, Unused, Unused]),
+    // object_wait_one, object_wait_many, object_get_info
+    (0x26, [Handle, Flags, Value, Ptr, Unused, Unused]),
+    (0x27, [Ptr, Len, Value, Unused, Unused, Unused]),
+    (0x28, [Handle, Value, Ptr, Len, Ptr, Ptr]),
+    // channel_write_etc, channel_read_etc, object_signal_peer
+    (0x29, [Handle, Flags, Ptr, Len, Ptr, Len]),
+    (0x2A, [Handle, Flags, Ptr, Len, Ptr, Len]),
+    (0x2B, [Handle, Flags, Flags, Unused, Unused, Unused]),
+    // counter_create, counter_add, counter_read, counter_write
+    (0x2C, [Flags, Unused, Unused, Unused, Unused, Unused]),
+    (0x2D, [Handle, Value, Unused, Unused, Unused, Unused]),
+    (0x2E, [Handle, Ptr, Unused, Unused, Unused, Unused]),
+    (0x2F, [Handle, Value, Unused, Unused, Unused, Unused]),
+    // handle_duplicate, handle_transfer, profile_create, object_set_profile,
+    // handle_close_many, object_get_child
+    (0x31, [Handle, Flags, Ptr, Unused, Unused, Unused]),
+    (0x32, [Handle, Flags, Flags, Unused, Unused, Unused]),
+    (0x33, [Handle, Ptr, Ptr, Unused, Unused, Unused]),
+    (0x34, [Handle, Handle, Flags, Unused, Unused, Unused]),
+    (0x35, [Ptr, Len, Unused, Unused, Unused, Unused]),
+    (0x36, [Handle, Value, Flags, Unused, Unused, Unused]),
+    // clock_get, timer_create, timer_set, timer_cancel
+    (0x40, [Value, Unused, Unused, Unused, Unused, Unused]),
+    (0x41, [Flags, Value, Unused, Unused, Unused, Unused]),
+    (0x42, [Handle, Value, Value, Unused, Unused, Unused]),
+    (0x43, [Handle, Unused, Unused, Unused, Unused, Unused]),
+    // channel_call_etc, with a zero deadline so it never waits
+    (0x50, [Handle, Flags, Unused, Ptr, Unused, Unused]),
+    // hypervisor_create, hypervisor_op, guest_set_trap, vcpu_create;
+    // vcpu_enter is left out as it runs the guest
+    (0x90, [Handle, Flags, Unused, Unused, Unused, Unused]),
+    (0x91, [Handle, Flags, Value, Handle, Len, Len]),
+    (0x92, [Handle, Value, Value, Len, Handle, Value]),
+    (0x93, [Handle, Flags, Value, Unused, Unused, Unused]),
+    // vcpu_kick, vcpu_interrupt, vcpu_read_state, vcpu_write_state
+    (0x95, [Handle, Unused, Unused, Unused, Unused, Unused]),
+    (0x96, [Handle, Value, Unused, Unused, Unused, Unused]),
+    (0x97, [Handle, Value, Ptr, Len, Unused, Unused]),
+    (0x98, [Handle, Value, Ptr, Len, Unused, Unused]),
+    // system_get_version, system_get_phys_mem
+    (0xA0, [Ptr, Unused, Unused, Unused, Unused, Unused]),
+    (0xA1, [Unused, Unused, Unused, Unused, Unused, Unused]),
+    // cprng_draw, cprng_add_entropy
+    (0xA3, [Ptr, Len, Unused, Unused, Unused, Unused]),
+    (0xA4, [Ptr, Len, Unused, Unused, Unused, Unused]),
+    // crashlog_read, kcounter_read, ktrace_read, ktrace_write
+    (0xA5, [Handle, Len, Ptr, Len, Ptr, Unused]),
+    (0xA6, [Handle, Ptr, Len, Ptr, Ptr, Unused]),
+    (0xA7, [Handle, Ptr, Len, Len, Ptr, Unused]),
+    (0xA9, [Handle, Value, Value, Value, Unused, Unused]),
+    // resource_create, system_get_event
+    (0xAA, [Handle, Flags, Value, Len, Ptr, Len]),
+    (0xAB, [Handle, Value, Unused, Unused, Unused, Unused]),
+    // system_get_features, system_get_num_cpus
+    (0xAC, [Value, Ptr, Len, Unused, Unused, Unused]),
+    (0xAD, [Unused, Unused, Unused, Unused, Unused, Unused]),
+    // vmo_transfer_data
+    (0xDB, [Handle, Flags, Len, Len, Handle, Len]),
+    // fifo_create, fifo_write, fifo_read
+    (0xF0, [Len, Len, Flags, Ptr, Ptr, Unused]),
+    (0xF1, [Handle, Len, Ptr, Len, Ptr, Unused]),
+    (0xF2, [Handle, Len, Ptr, Len, Ptr, Unused]),
+];
+
+/// SplitMix64, which is enough to spread a seed and an index into a case
+struct Rng(u64);
+
+impl Rng {
+    /// Generator for case `index` of a run seeded with `seed`
+    fn for_case(seed: u64, index: u64) -> Self {
+        let mut rng = Self(seed ^ index.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        rng.next();
+        rng
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn pick(&mut self, values: &[u64]) -> u64 {
+        values[self.below(values.len() as u64) as usize]
+    }
+}
+
+/// One generated syscall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Case {
+    number: u32,
+    args: [u64; 6],
+}
+
+impl Case {
+    /// Case `index` of the run seeded with `seed`
+    fn generate(seed: u64, index: u64) -> Self {
+        let mut rng = Rng::for_case(seed, index);
+
+        if rng.below(10) == 0 {
+            let number = unassigned(&mut rng);
+            let args = [(); 6].map(|_| rng.next());
+            return Self { number, args };
+        }
+
+        let (number, kinds) = SPECS[rng.below(SPECS.len() as u64) as usize];
+        let mut args = [0u64; 6];
+        for (arg, kind) in args.iter_mut().zip(kinds) {
+            *arg = draw(&mut rng, kind);
+        }
+        Self { number, args }
+    }
+
+    fn run(&self) -> SyscallRet {
+        syscall_dispatch(SyscallArgs::new(self.number, self.args.map(|a| a as usize)))
+    }
+}
+
+/// A syscall number outside every assigned range
+fn unassigned(rng: &mut Rng) -> u32 {
+    loop {
+        // Mostly near the assigned ranges, where off-by-one range bugs live
+        let number = if rng.below(2) == 0 { rng.below(0x100) as u32 } else { rng.next() as u32 };
+        if SyscallNumber::from_raw(number) == SyscallNumber::Unknown {
+            return number;
+        }
+    }
+}
+
+/// An argument of kind `kind`
+fn draw(rng: &mut Rng, kind: Arg) -> u64 {
+    match kind {
+        Handle => match rng.below(5) {
+            0 => 0,
+            1 | 2 => 1 + rng.below(64),
+            3 => u32::MAX as u64,
+            4 => rng.next() & 0xffff_ffff,
+            _ => rng.next(),
+        },
+        Ptr => match rng.below(4) {
+            0 => 0,
+            1 => KERNEL_HALF + (rng.next() & 0x7f_ffff_ffff),
+            2 => u64::MAX - rng.below(64),
+            _ => KERNEL_HALF + rng.below(8),
+        },
+        Len => match rng.below(6) {
+            0 => 0,
+            1 => 1,
+            2 | 3 => rng.pick(BOUNDARY_LENS),
+            4 => rng.below(1024) * 4096,
+            _ => {
+                let random = rng.next();
+                rng.pick(&[u32::MAX as u64, 1 << 63, u64::MAX, random])
+            }
+        },
+        Flags => match rng.below(4) {
+            0 => 0,
+            1 => 1 << rng.below(32),
+            2 => u32::MAX as u64,
+            _ => rng.next() & 0xffff_ffff,
+        },
+        Value => match rng.below(4) {
+            0 => 0,
+            1 => 1,
+            2 => u64::MAX,
+            _ => rng.next(),
+        },
+        Unused => 0,
+    }
+}
+
+/// Entries in the handle table and the object registries
+fn live_objects() -> usize {
+    let events = event::get_stats();
+    crate::kernel::thread::current_thread_handle_table().count()
+        + vmo::get_stats().total_vmos
+        + channel::get_stats().total_channels
+        + events.total_events
+        + events.total_eventpairs
+        + timer::get_stats().total_timers
+        + fifo::get_stats().total_fifos
+}
+
+/// Outcome of a fuzzing run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FuzzSummary {
+    pub cases: u64,
+    pub ok: u64,
+    pub errors: u64,
+    pub failures: u64,
+}
+
+/// Run `count` cases from `seed`, starting at case `first`
+///
+/// With `verbose`, each case is logged before it is issued.
+pub fn fuzz(seed: u64, first: u64, count: u64, verbose: bool) -> FuzzSummary {
+    println!("SYSFUZZ START seed={:#x} cases={}", seed, count);
+
+    let start = ktimer::current_time();
+    let mut summary = FuzzSummary::default();
+
+    for n in first..first + count {
+        let case = Case::generate(seed, n);
+        if verbose {
+            let a = case.args;
+            println!(
+                "SYSFUZZ CASE n={} nr={:#x} args=[{:#x}, {:#x}, {:#x}, {:#x}, {:#x}, {:#x}]",
+                n, case.number, a[0], a[1], a[2], a[3], a[4], a[5]
+            );
+        }
+
+        let before = live_objects();
+        let ret = case.run();
+        let after = live_objects();
+
+        summary.cases += 1;
+        if ret >= 0 {
+            summary.ok += 1;
+        } else {
+            summary.errors += 1;
+        }
+
+        if SyscallNumber::from_raw(case.number) == SyscallNumber::Unknown
+            && ret != syscalls::err_to_ret(RX_ERR_NOT_SUPPORTED)
+        {
+            summary.failures += 1;
+            println!("SYSFUZZ UNKNOWN n={} nr={:#x} ret={}", n, case.number, ret);
+        }
+        if ret < 0 && after != before {
+            summary.failures += 1;
+            println!("SYSFUZZ LEAK n={} nr={:#x} ret={} objects={}->{}", n, case.number, ret, before, after);
+        }
+    }
+
+    println!(
+        "SYSFUZZ END cases={} ok={} errors={} failures={} ns={}",
+        summary.cases,
+        summary.ok,
+        summary.errors,
+        summary.failures,
+        ktimer::current_time() - start
+    );
+    summary
+}
+
+/// A seed from the CPRNG, for runs that don't name one
+fn random_seed() -> u64 {
+    let mut bytes = [0u8; 8];
+    crypto::draw(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// Fuzz with the seed in `kernel.sysfuzz` and exit, if it is set
+pub fn run_boot_fuzz() {
+    if cmdline::cmdline_get(CMDLINE_SEED).is_none() {
+        return;
+    }
+
+    let seed = cmdline::cmdline_get_uint64(CMDLINE_SEED, 0);
+    let cases = cmdline::cmdline_get_uint64(CMDLINE_CASES, DEFAULT_CASES);
+    let summary = fuzz(seed, 0, cases, false);
+    runner::exit_qemu(summary.failures == 0);
+}
+
+/// Parse a decimal or `0x` hex number
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// `sysfuzz` console command
+fn cmd_sysfuzz(_argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    const USAGE: &str = "usage: sysfuzz [-v] [seed [cases]] | sysfuzz replay <seed> <case>";
+
+    let summary = match argv.get(1..).unwrap_or(&[]) {
+        ["replay", seed, case] => match (parse_u64(seed), parse_u64(case)) {
+            (Some(seed), Some(case)) => fuzz(seed, case, 1, true),
+            _ => {
+                println!("{}", USAGE);
+                return 1;
+            }
+        },
+        args => {
+            let (verbose, args) = match args {
+                ["-v", rest @ ..] => (true, rest),
+                _ => (false, args),
+            };
+            let seed = match args.first() {
+                Some(s) => parse_u64(s),
+                None => Some(random_seed()),
+            };
+            let cases = match args.get(1) {
+                Some(s) => parse_u64(s),
+                None => Some(DEFAULT_CASES),
+            };
+            match (seed, cases, args.len()) {
+                (Some(seed), Some(cases), 0..=2) => fuzz(seed, 0, cases, verbose),
+                _ => {
+                    println!("{}", USAGE);
+                    return 1;
+                }
+            }
+        }
+    };
+
+    if summary.failures == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+crate::static_command!("sysfuzz", "fuzz the syscall dispatcher (see kernel.sysfuzz)", cmd_sysfuzz);
+
+/// A short fixed-seed run must not leak or misreport unknown syscalls
+#[test_case]
+fn sysfuzz_smoke() -> TestResult {
+    let summary = fuzz(0x5eed, 0, 500, false);
+    if summary.failures != 0 {
+        return Err(format!("{} of {} cases failed", summary.failures, summary.cases));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cases_are_reproducible() {
+        for n in 0..64 {
+            assert_eq!(Case::generate(42, n), Case::generate(42, n));
+        }
+        assert_ne!(Case::generate(42, 0), Case::generate(43, 0));
+    }
+
+    #[test]
+    fn test_pointers_never_user() {
+        let mut rng = Rng::for_case(7, 0);
+        for _ in 0..1000 {
+            let ptr = draw(&mut rng, Ptr);
+            assert!(ptr == 0 || ptr >= KERNEL_HALF);
+        }
+    }
+
+    #[test]
+    fn test_unassigned_numbers() {
+        let mut rng = Rng::for_case(7, 0);
+        for _ in 0..1000 {
+            assert_eq!(SyscallNumber::from_raw(unassigned(&mut rng)), SyscallNumber::Unknown);
+        }
+    }
+
+    #[test]
+    fn test_specs_are_assigned() {
+        for (number, _) in SPECS {
+            assert_ne!(SyscallNumber::from_raw(*number), SyscallNumber::Unknown);
+        }
+    }
+
+    #[test]
+    fn test_parse_u64() {
+        assert_eq!(parse_u64("42"), Some(42));
+        assert_eq!(parse_u64("0x5eed"), Some(0x5eed));
+        assert_eq!(parse_u64("seed"), None);
+    }
+}
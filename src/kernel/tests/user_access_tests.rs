@@ -0,0 +1,178 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! User Access Protection Tests
+//!
+//! Tests that the kernel can't execute or dereference user memory by
+//! accident: SMEP/SMAP on x86_64, PAN on arm64, the usercopy access window
+//! and W^X enforcement in the VMAR layer.
+
+
+use crate::kernel::tests::runner::*;
+use crate::kernel::syscalls::vmar::{vmar_options, Vmar};
+use crate::kernel::usercopy::{copy_from_user, copy_to_user, UserAccessWindow, UserPtr};
+
+// Import logging and assertion macros at crate level
+use crate::{assert_false, assert_true, log_debug, log_info};
+
+/// Kernel address that must never be accepted as a user pointer
+#[cfg(target_arch = "x86_64")]
+const KERNEL_ADDR: usize = 0xffff_8000_0000_1000;
+#[cfg(not(target_arch = "x86_64"))]
+const KERNEL_ADDR: usize = 0xffff_0000_0000_1000;
+
+/// Returns true if the kernel is currently blocked from touching user pages
+#[cfg(target_arch = "x86_64")]
+fn user_access_blocked() -> bool {
+    use crate::kernel::arch::amd64::registers::X86_FLAGS_AC;
+
+    let rflags: u64;
+    unsafe {
+        core::arch::asm!("pushfq; pop {}", out(reg) rflags, options(nomem));
+    }
+    rflags & X86_FLAGS_AC == 0
+}
+
+/// Returns true if the kernel is currently blocked from touching user pages
+#[cfg(target_arch = "aarch64")]
+fn user_access_blocked() -> bool {
+    // mrs x0, pan
+    let pan: u64;
+    unsafe {
+        core::arch::asm!(".inst 0xd5384260", out("x0") pan, options(nomem, nostack));
+    }
+    pan & (1 << 22) != 0
+}
+
+/// Returns true if the kernel is currently blocked from touching user pages
+#[cfg(target_arch = "riscv64")]
+fn user_access_blocked() -> bool {
+    true
+}
+
+/// Returns true if this CPU enforces user access prevention at all
+fn user_access_prevention_enabled() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    return crate::kernel::arch::amd64::mmu::x86_smap_enabled();
+
+    #[cfg(target_arch = "aarch64")]
+    return crate::kernel::arch::arm64::mmu::arm64_pan_enabled();
+
+    #[cfg(target_arch = "riscv64")]
+    return false;
+}
+
+/// Test that SMEP/SMAP are on whenever the CPU supports them
+#[cfg(target_arch = "x86_64")]
+fn smep_smap_enabled_test() -> TestResult {
+    use crate::kernel::arch::amd64::{feature, mmu};
+
+    if feature::x86_feature_smep() {
+        assert_true!(mmu::x86_smep_enabled(), "SMEP supported but not enabled");
+    } else {
+        log_debug!("SMEP not supported on this CPU");
+    }
+
+    if feature::x86_feature_smap() {
+        assert_true!(mmu::x86_smap_enabled(), "SMAP supported but not enabled");
+    } else {
+        log_debug!("SMAP not supported on this CPU");
+    }
+
+    log_info!("SMEP/SMAP test passed");
+    Ok(())
+}
+
+/// Test that PAN is on whenever the CPU supports it
+#[cfg(target_arch = "aarch64")]
+fn pan_enabled_test() -> TestResult {
+    use crate::kernel::arch::arm64::mmu;
+
+    if !mmu::arm64_pan_enabled() {
+        log_debug!("PAN not supported on this CPU");
+        return Ok(());
+    }
+
+    assert_true!(user_access_blocked(), "PSTATE.PAN should be set in kernel code");
+
+    log_info!("PAN test passed");
+    Ok(())
+}
+
+/// Test that the access window opens and closes user access
+fn access_window_test() -> TestResult {
+    if !user_access_prevention_enabled() {
+        log_debug!("No user access prevention on this CPU, skipping");
+        return Ok(());
+    }
+
+    assert_true!(user_access_blocked(), "user access open outside a window");
+    {
+        let _window = UserAccessWindow::open();
+        assert_false!(user_access_blocked(), "user access still blocked inside the window");
+    }
+    assert_true!(user_access_blocked(), "window left open after drop");
+
+    log_info!("Access window test passed");
+    Ok(())
+}
+
+/// Test that kernel addresses are never accepted as user pointers
+fn kernel_pointer_rejected_test() -> TestResult {
+    let mut buf = [0u8; 16];
+    let ptr = UserPtr::<u8>::new(KERNEL_ADDR);
+
+    unsafe {
+        assert_true!(copy_from_user(buf.as_mut_ptr(), ptr, buf.len()).is_err());
+        assert_true!(copy_to_user(ptr, buf.as_ptr(), buf.len()).is_err());
+    }
+
+    // The window must be closed again after a rejected copy
+    if user_access_prevention_enabled() {
+        assert_true!(user_access_blocked());
+    }
+
+    log_info!("Kernel pointer rejection test passed");
+    Ok(())
+}
+
+/// Test that W^X is enforced for mappings
+fn wxorx_test() -> TestResult {
+    let rwx = vmar_options::PERM_READ | vmar_options::PERM_WRITE | vmar_options::PERM_EXECUTE;
+    let root = Vmar::new_root(0x1000, 0x100000);
+
+    assert_false!(root.can_map(rwx), "root VMAR allows W+X");
+    assert_true!(root.can_map(vmar_options::PERM_READ | vmar_options::PERM_EXECUTE));
+
+    log_info!("W^X test passed");
+    Ok(())
+}
+
+/// Create the user access test suite
+pub fn create_user_access_suite() -> TestSuite {
+    let mut tests = alloc::vec::Vec::from([
+        TestCase::new("access_window", "User access window", access_window_test),
+        TestCase::new("kernel_pointer", "Kernel pointers rejected", kernel_pointer_rejected_test),
+        TestCase::new("wxorx", "W^X enforcement", wxorx_test),
+    ]);
+
+    #[cfg(target_arch = "x86_64")]
+    tests.insert(0, TestCase::new("smep_smap", "SMEP/SMAP enabled", smep_smap_enabled_test));
+
+    #[cfg(target_arch = "aarch64")]
+    tests.insert(0, TestCase::new("pan", "PAN enabled", pan_enabled_test));
+
+    TestSuite::new(
+        "user_access",
+        "User memory access and execution protection tests",
+        tests,
+    )
+}
+
+/// Register user access tests
+pub fn register() {
+    register_suite(create_user_access_suite());
+}
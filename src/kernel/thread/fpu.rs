@@ -0,0 +1,245 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Lazy FPU and Vector State
+//!
+//! Each thread has its own save area for the FPU and vector registers,
+//! allocated the first time it uses them. Threads are switched in with
+//! the FPU disabled; their first FPU or vector instruction traps and
+//! [`first_use`] loads their state. Threads that never touch the FPU
+//! never pay for saving or restoring it.
+//!
+//! # Design
+//!
+//! - **Save on switch out**: [`switch_out`] saves the state only if the
+//!   FPU is enabled, i.e. the thread used it since it was switched in,
+//!   then disables it. A thread that moves to another CPU always finds its
+//!   latest state in its save area.
+//! - **Skipped reloads**: Each CPU remembers whose state its registers
+//!   still hold. When that thread traps again on the same CPU and nobody
+//!   else has loaded theirs in between, the trap only re-enables the FPU.
+//! - **Debuggers**: [`with_state`] gives the debugger register-state API
+//!   the save area, saving the live registers first if the thread is the
+//!   current one. After a write the copy held in CPU registers is
+//!   forgotten, so the next trap loads what the debugger wrote.
+//!
+//! The save area format and the instructions come from [`ArchFpu`]:
+//! XSAVE or FXSAVE on x86, FPSIMD and SVE on arm64, and F/D and V on
+//! RISC-V.
+
+use crate::kernel::arch::arch_traits::ArchFpu;
+use crate::kernel::percpu::{self, SMP_MAX_CPUS};
+use crate::kernel::thread::{self, Thread, ThreadId, TID_INVALID};
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+#[cfg(target_arch = "x86_64")]
+type Arch = crate::kernel::arch::amd64::Amd64Arch;
+
+#[cfg(target_arch = "aarch64")]
+type Arch = crate::kernel::arch::arm64::Arm64Arch;
+
+#[cfg(target_arch = "riscv64")]
+type Arch = crate::kernel::arch::riscv64::Riscv64Arch;
+
+/// The architecture's save area
+pub type FpuState = <Arch as ArchFpu>::FpuState;
+
+KCOUNTER!(FPU_TRAPS, "kernel.fpu.traps");
+KCOUNTER!(FPU_RESTORES, "kernel.fpu.restores");
+KCOUNTER!(FPU_SAVES, "kernel.fpu.saves");
+
+/// `loaded_on` of a state no CPU holds
+const NO_CPU: u32 = u32::MAX;
+
+/// Thread whose state each CPU's registers hold, `TID_INVALID` if none
+static LOADED: [AtomicU64; SMP_MAX_CPUS] = [const { AtomicU64::new(TID_INVALID) }; SMP_MAX_CPUS];
+
+/// A thread's FPU and vector state
+pub struct ThreadFpu {
+    /// Save area, null until the thread first uses the FPU
+    area: AtomicPtr<u8>,
+
+    /// CPU whose registers last had this state loaded, `NO_CPU` if none
+    loaded_on: AtomicU32,
+}
+
+impl ThreadFpu {
+    /// A thread that has not used the FPU
+    pub const fn new() -> Self {
+        Self {
+            area: AtomicPtr::new(core::ptr::null_mut()),
+            loaded_on: AtomicU32::new(NO_CPU),
+        }
+    }
+
+    /// Whether the thread has used the FPU, or had its state written
+    pub fn used(&self) -> bool {
+        !self.area.load(Ordering::Acquire).is_null()
+    }
+
+    fn layout() -> Layout {
+        let align = <Arch as ArchFpu>::state_align().max(16);
+        Layout::from_size_align(<Arch as ArchFpu>::state_size(), align)
+            .expect("FPU save area layout")
+    }
+
+    /// The save area, allocated in the initial state on first use
+    ///
+    /// Returns `None` if it could not be allocated.
+    fn area(&self) -> Option<*mut FpuState> {
+        let area = self.area.load(Ordering::Acquire);
+        if !area.is_null() {
+            return Some(area.cast());
+        }
+
+        let layout = Self::layout();
+        let new = unsafe { alloc_zeroed(layout) };
+        if new.is_null() {
+            return None;
+        }
+        unsafe { <Arch as ArchFpu>::init_state(new.cast()) };
+
+        match self.area.compare_exchange(
+            core::ptr::null_mut(),
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => Some(new.cast()),
+            Err(existing) => {
+                unsafe { dealloc(new, layout) };
+                Some(existing.cast())
+            }
+        }
+    }
+}
+
+impl Drop for ThreadFpu {
+    fn drop(&mut self) {
+        let area = *self.area.get_mut();
+        if !area.is_null() {
+            unsafe { dealloc(area, Self::layout()) };
+        }
+    }
+}
+
+/// Whether a trap by `tid` on `cpu` has to load the save area
+///
+/// Not if the CPU's registers still hold the thread's state and that is
+/// where the thread last loaded it.
+fn needs_restore(loaded: ThreadId, loaded_on: u32, tid: ThreadId, cpu: u32) -> bool {
+    loaded != tid || loaded_on != cpu
+}
+
+/// Save `thread`'s state if it used the FPU since it was switched in,
+/// and disable the FPU for the next thread
+///
+/// Called from the architecture's context switch, with interrupts
+/// disabled.
+pub fn switch_out(thread: &Thread) {
+    if !<Arch as ArchFpu>::is_enabled() {
+        return;
+    }
+
+    let area = thread.fpu.area.load(Ordering::Acquire);
+    if !area.is_null() {
+        unsafe { <Arch as ArchFpu>::save(area.cast()) };
+        FPU_SAVES.add(1);
+    }
+    unsafe { <Arch as ArchFpu>::disable() };
+}
+
+/// Give the FPU to `thread` after it trapped on an FPU or vector
+/// instruction
+///
+/// Called from the architecture's trap handler for user mode. Returns
+/// false if the save area could not be allocated; the caller treats the
+/// instruction as faulting.
+pub fn first_use(thread: &Thread) -> bool {
+    FPU_TRAPS.add(1);
+    let Some(area) = thread.fpu.area() else {
+        return false;
+    };
+
+    let cpu = percpu::current_cpu_num();
+    unsafe { <Arch as ArchFpu>::enable() };
+
+    let slot = &LOADED[cpu as usize];
+    let loaded_on = thread.fpu.loaded_on.load(Ordering::Relaxed);
+    if needs_restore(slot.load(Ordering::Relaxed), loaded_on, thread.tid, cpu) {
+        unsafe { <Arch as ArchFpu>::restore(area) };
+        slot.store(thread.tid, Ordering::Relaxed);
+        thread.fpu.loaded_on.store(cpu, Ordering::Relaxed);
+        FPU_RESTORES.add(1);
+    }
+    true
+}
+
+/// Run `f` on `thread`'s save area, for the debugger to read or write
+///
+/// If `thread` is the current thread with its state live in the
+/// registers, the registers are saved first, and loaded again after a
+/// write. Otherwise a write makes the thread's next trap reload the area.
+///
+/// Returns `None` if the save area could not be allocated.
+pub fn with_state<R>(thread: &Thread, write: bool, f: impl FnOnce(*mut FpuState) -> R) -> Option<R> {
+    let area = thread.fpu.area()?;
+    let live = thread::current_thread_id() == thread.tid && <Arch as ArchFpu>::is_enabled();
+
+    if live {
+        unsafe { <Arch as ArchFpu>::save(area) };
+    }
+    let result = f(area);
+    if write {
+        thread.fpu.loaded_on.store(NO_CPU, Ordering::Relaxed);
+        if live {
+            unsafe { <Arch as ArchFpu>::restore(area) };
+        }
+    }
+    Some(result)
+}
+
+/// Times threads trapped on first use of the FPU
+pub fn trap_count() -> i64 {
+    FPU_TRAPS.value()
+}
+
+/// Times a trap had to load a save area
+pub fn restore_count() -> i64 {
+    FPU_RESTORES.value()
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_restore() {
+        // Still loaded here
+        assert!(!needs_restore(7, 2, 7, 2));
+        // Another thread loaded its state since
+        assert!(needs_restore(8, 2, 7, 2));
+        // The thread loaded its state on another CPU since
+        assert!(needs_restore(7, 3, 7, 2));
+        // Forgotten after a debugger write
+        assert!(needs_restore(7, NO_CPU, 7, 2));
+    }
+
+    #[test]
+    fn test_unused_thread_has_no_area() {
+        let fpu = ThreadFpu::new();
+        assert!(!fpu.used());
+        assert_eq!(fpu.loaded_on.load(Ordering::Relaxed), NO_CPU);
+    }
+}
@@ -46,13 +46,24 @@ use crate::kernel::vm::{VmError, Result};
 use crate::kernel::arch::arch_traits::*;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use crate::kernel::lib::slab::ObjectCache;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use crate::kernel::sync::spin::SpinMutex;
 use crate::kernel::sync::Mutex;
+use crate::kernel::object::koid::{alloc_koid, Koid, KOID_INVALID};
 use alloc::vec::Vec;
 use alloc::string::String;
 use crate::rustux::types::*;
 
+pub mod fpu;
+pub mod runtime;
+
+pub use fpu::ThreadFpu;
+pub use runtime::{RuntimeTotals, TaskRuntimeInfo, ThreadRuntime};
+
+use crate::kernel::sched::deadline::DeadlineState;
+use crate::kernel::numa::{self, NUMA_NODE_ANY};
+
 // Import logging macros
 use crate::{log_debug, log_info, log_trace};
 
@@ -196,14 +207,17 @@ pub struct Thread {
     /// Thread ID
     pub tid: ThreadId,
 
+    /// Kernel object ID
+    pub koid: Koid,
+
     /// Thread state
     pub state: Mutex<ThreadState>,
 
     /// Thread priority
-    pub priority: ThreadPriority,
+    pub priority: AtomicU8,
 
     /// CPU affinity mask
-    pub cpu_affinity: CpuMask,
+    pub cpu_affinity: AtomicU64,
 
     /// Block reason (if blocked)
     pub block_reason: Mutex<BlockReason>,
@@ -243,6 +257,27 @@ pub struct Thread {
 
     /// Argument to pass to entry point
     pub entry_arg: usize,
+
+    /// CPU and queue time, updated by the scheduler
+    pub runtime: ThreadRuntime,
+
+    /// FPU and vector registers, loaded lazily
+    pub fpu: ThreadFpu,
+
+    /// Deadline class state, if a deadline profile is applied
+    pub deadline: Mutex<Option<DeadlineState>>,
+
+    /// Kept off every CPU while the system suspends
+    pub frozen: AtomicBool,
+
+    /// Priority lent by a blocked channel caller, 0 if none
+    pub lent_priority: AtomicU8,
+
+    /// Transaction the lent priority is for; the reply gives it back
+    pub lent_txid: AtomicU32,
+
+    /// NUMA node the scheduler prefers to run the thread on
+    pub home_node: AtomicU32,
 }
 
 /// Architecture-specific thread context
@@ -276,8 +311,7 @@ pub struct ArchData {
     #[cfg(target_arch = "aarch64")]
     pub sp: VAddr,
 
-    /// Stack guard value (for aarch64)
-    #[cfg(target_arch = "aarch64")]
+    /// Stack canary guard value for this thread
     pub stack_guard: u64,
 
     /// Unsafe stack pointer (for aarch64)
@@ -304,10 +338,6 @@ pub struct ArchData {
     #[cfg(target_arch = "aarch64")]
     pub debug_state: crate::kernel::arch::arm64::thread::Arm64DebugState,
 
-    /// FP state pointer (for aarch64)
-    #[cfg(target_arch = "aarch64")]
-    pub fpstate: *mut core::ffi::c_void,
-
     /// Pointer to suspended general registers
     #[cfg(target_arch = "x86_64")]
     pub suspended_general_regs: *const crate::kernel::arch::amd64::X86ThreadStateGeneralRegs,
@@ -332,10 +362,6 @@ pub struct ArchData {
     #[cfg(target_arch = "x86_64")]
     pub track_debug_state: bool,
 
-    /// Extended register state (FXSAVE area) for x86_64
-    #[cfg(target_arch = "x86_64")]
-    pub extended_register_state: *mut core::ffi::c_void,
-
     /// Pointer to suspended general registers
     #[cfg(target_arch = "riscv64")]
     pub suspended_general_regs: *const crate::kernel::arch::riscv64::exceptions_c::RiscvIframe,
@@ -349,7 +375,6 @@ impl ArchData {
             suspended_general_regs: core::ptr::null_mut(),
             #[cfg(target_arch = "aarch64")]
             sp: 0,
-            #[cfg(target_arch = "aarch64")]
             stack_guard: 0,
             #[cfg(target_arch = "aarch64")]
             unsafe_sp: 0,
@@ -363,8 +388,6 @@ impl ArchData {
             thread_pointer_location: 0u64,
             #[cfg(target_arch = "aarch64")]
             debug_state: unsafe { core::mem::zeroed() },
-            #[cfg(target_arch = "aarch64")]
-            fpstate: core::ptr::null_mut(),
             #[cfg(target_arch = "x86_64")]
             suspended_general_regs: core::ptr::null(),
             #[cfg(target_arch = "x86_64")]
@@ -377,8 +400,6 @@ impl ArchData {
             debug_state: unsafe { core::mem::zeroed() },
             #[cfg(target_arch = "x86_64")]
             track_debug_state: false,
-            #[cfg(target_arch = "x86_64")]
-            extended_register_state: core::ptr::null_mut(),
             #[cfg(target_arch = "riscv64")]
             suspended_general_regs: core::ptr::null(),
         }
@@ -402,24 +423,35 @@ impl Thread {
     ) -> Result<Self> {
         let tid = TID_ALLOCATOR.allocate();
 
-        let thread = Self {
+        let mut arch = ArchData::new();
+        arch.stack_guard = crate::kernel::lib::ssp::new_thread_stack_guard();
+
+        let mut thread = Self {
             tid,
+            koid: alloc_koid(),
             state: Mutex::new(ThreadState::New),
-            priority,
-            cpu_affinity: CPU_MASK_ALL,
+            priority: AtomicU8::new(priority),
+            cpu_affinity: AtomicU64::new(CPU_MASK_ALL),
             block_reason: Mutex::new(BlockReason::None),
             stack: Mutex::new(None),
             pid: Mutex::new(None),
             parent_tid: Mutex::new(None),
             return_code: Mutex::new(None),
             arch_context: Mutex::new(None),
-            arch: ArchData::new(),
+            arch,
             name: Mutex::new(None),
             joinable: AtomicBool::new(false),
             join_waiters: Mutex::new(Vec::new()),
             ref_count: AtomicU64::new(1),
             entry_point,
             entry_arg: arg,
+            runtime: ThreadRuntime::new(),
+            fpu: ThreadFpu::new(),
+            deadline: Mutex::new(None),
+            frozen: AtomicBool::new(false),
+            lent_priority: AtomicU8::new(0),
+            lent_txid: AtomicU32::new(0),
+            home_node: AtomicU32::new(Self::initial_home_node()),
         };
 
         // Initialize architecture-specific context
@@ -437,9 +469,11 @@ impl Thread {
             });
         }
 
-        // Initialize context with entry point and stack
-        // This would call into arch-specific code
-        // For now, leave it zero-initialized
+        // The first switch to the thread starts it at its entry point
+        #[cfg(target_arch = "x86_64")]
+        if stack_top != 0 {
+            unsafe { crate::kernel::arch::amd64::arch_thread_initialize(&mut thread, stack_top) };
+        }
 
         log_debug!(
             "Created thread: tid={} entry={:#x} priority={}",
@@ -460,12 +494,14 @@ impl Thread {
         // Allocate kernel stack
         let stack = alloc_kernel_stack(0)?; // Owner ID 0 = kernel
 
-        Self::new(
+        let thread = Self::new(
             entry_point as VAddr,
             arg,
             stack.top,
             priority,
-        )
+        )?;
+        thread.set_stack(stack);
+        Ok(thread)
     }
 
     /// Get the thread ID
@@ -473,25 +509,119 @@ impl Thread {
         self.tid
     }
 
+    /// Get the kernel object ID
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
     /// Get the thread state
     pub fn state(&self) -> ThreadState {
         *self.state.lock()
     }
 
     /// Set the thread state
+    ///
+    /// Becoming ready starts the thread's queue time.
     pub fn set_state(&self, new_state: ThreadState) {
         *self.state.lock() = new_state;
+        if new_state == ThreadState::Ready {
+            self.runtime.mark_ready(crate::kernel::timer::current_time());
+        }
+    }
+
+    /// CPU time, queue time and context switches so far
+    pub fn runtime(&self) -> TaskRuntimeInfo {
+        self.runtime.snapshot(crate::kernel::timer::current_time())
     }
 
     /// Get the thread priority
     pub fn priority(&self) -> ThreadPriority {
-        self.priority
+        self.priority.load(Ordering::Relaxed)
     }
 
     /// Set the thread priority
-    pub fn set_priority(&mut self, priority: ThreadPriority) {
+    pub fn set_priority(&self, priority: ThreadPriority) {
         // Would need to update scheduler runqueue
-        self.priority = priority;
+        self.priority.store(priority, Ordering::Relaxed);
+    }
+
+    /// The priority the scheduler runs this thread at: its own, or a
+    /// higher one lent to it
+    pub fn effective_priority(&self) -> ThreadPriority {
+        self.priority().max(self.lent_priority.load(Ordering::Relaxed))
+    }
+
+    /// Priority lent to this thread for transaction `txid`, if any
+    pub fn lent_priority(&self) -> Option<(ThreadPriority, u32)> {
+        match self.lent_priority.load(Ordering::Relaxed) {
+            0 => None,
+            priority => Some((priority, self.lent_txid.load(Ordering::Relaxed))),
+        }
+    }
+
+    /// Run at least at `priority` until transaction `txid` is answered
+    ///
+    /// Replaces any earlier loan. Use
+    /// [`sched::lend_priority`](crate::kernel::sched::lend_priority) to
+    /// also move the thread within the run queue.
+    pub fn set_lent_priority(&self, priority: ThreadPriority, txid: u32) {
+        self.lent_txid.store(txid, Ordering::Relaxed);
+        self.lent_priority.store(priority, Ordering::Relaxed);
+    }
+
+    /// Home node of a new thread: its creator's, on NUMA machines
+    fn initial_home_node() -> u32 {
+        if numa::node_count() > 1 {
+            numa::current_node()
+        } else {
+            NUMA_NODE_ANY
+        }
+    }
+
+    /// NUMA node the thread prefers, `NUMA_NODE_ANY` if none
+    pub fn home_node(&self) -> u32 {
+        self.home_node.load(Ordering::Relaxed)
+    }
+
+    /// Prefer running on NUMA `node`, or `NUMA_NODE_ANY` for no preference
+    pub fn set_home_node(&self, node: u32) {
+        self.home_node.store(node, Ordering::Relaxed);
+    }
+
+    /// Give back a lent priority
+    pub fn clear_lent_priority(&self) {
+        self.lent_priority.store(0, Ordering::Relaxed);
+    }
+
+    /// Get the CPUs this thread may run on
+    pub fn cpu_affinity(&self) -> CpuMask {
+        self.cpu_affinity.load(Ordering::Relaxed)
+    }
+
+    /// Set the CPUs this thread may run on
+    ///
+    /// Use [`sched::set_affinity`](crate::kernel::sched::set_affinity) to
+    /// check the mask and move the thread off CPUs it leaves.
+    pub fn set_cpu_affinity(&self, mask: CpuMask) {
+        self.cpu_affinity.store(mask, Ordering::Relaxed);
+    }
+
+    /// Whether this thread may run on `cpu`
+    pub fn can_run_on(&self, cpu: u32) -> bool {
+        !self.is_frozen() && cpu < 64 && self.cpu_affinity() & (1 << cpu) != 0
+    }
+
+    /// Whether this thread is frozen for system suspend
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Acquire)
+    }
+
+    /// Freeze or thaw this thread
+    ///
+    /// Use [`sched::freeze_user_threads`](crate::kernel::sched::freeze_user_threads)
+    /// to also take a frozen thread off the CPU it is running on.
+    pub fn set_frozen(&self, frozen: bool) {
+        self.frozen.store(frozen, Ordering::Release);
     }
 
     /// Associate a process with this thread
@@ -588,7 +718,10 @@ impl Thread {
     /// Yield the current thread
     ///
     /// Voluntarily give up the CPU to other threads.
+    #[track_caller]
     pub fn yield_current() {
+        crate::kernel::preempt::check_schedule();
+
         // Get current thread
         // let current = scheduler::current_thread();
         // scheduler::yield_current();
@@ -631,9 +764,10 @@ impl Thread {
         // This is unsafe but necessary for low-level FPU state management
         static mut DUMMY_THREAD: Thread = Thread {
             tid: 0,
+            koid: KOID_INVALID,
             state: Mutex::new(ThreadState::Ready),
-            priority: PRIORITY_DEFAULT,
-            cpu_affinity: CPU_MASK_ALL,
+            priority: AtomicU8::new(PRIORITY_DEFAULT),
+            cpu_affinity: AtomicU64::new(CPU_MASK_ALL),
             block_reason: Mutex::new(BlockReason::None),
             stack: Mutex::new(None),
             pid: Mutex::new(None),
@@ -647,12 +781,36 @@ impl Thread {
             ref_count: AtomicU64::new(1),
             entry_point: 0,
             entry_arg: 0,
+            runtime: ThreadRuntime::new(),
+            fpu: ThreadFpu::new(),
+            deadline: Mutex::new(None),
+            frozen: AtomicBool::new(false),
+            lent_priority: AtomicU8::new(0),
+            lent_txid: AtomicU32::new(0),
+            home_node: AtomicU32::new(NUMA_NODE_ANY),
         };
 
         unsafe { &mut DUMMY_THREAD }
     }
 }
 
+/// ============================================================================
+/// Thread Allocation
+/// ============================================================================
+
+/// Cache threads are allocated from
+static THREAD_CACHE: ObjectCache = ObjectCache::for_arc::<Thread>("thread");
+
+/// Shared reference to a thread in the thread cache
+pub type ThreadRef = Arc<Thread, &'static ObjectCache>;
+
+impl Thread {
+    /// Move the thread into the thread cache, ready to register
+    pub fn into_ref(self) -> ThreadRef {
+        Arc::new_in(self, &THREAD_CACHE)
+    }
+}
+
 /// ============================================================================
 /// Thread Registry
 /// ============================================================================
@@ -663,7 +821,7 @@ const MAX_THREADS: usize = 65536;
 /// Thread registry for lookup by ID
 struct ThreadRegistry {
     /// Thread entries indexed by TID
-    entries: Mutex<BTreeMap<ThreadId, Arc<Thread>>>,
+    entries: Mutex<BTreeMap<ThreadId, ThreadRef>>,
 
     /// Number of active threads
     count: AtomicUsize,
@@ -677,17 +835,22 @@ impl ThreadRegistry {
         }
     }
 
-    fn insert(&self, thread: Arc<Thread>) {
+    fn insert(&self, thread: ThreadRef) {
         let mut entries = self.entries.lock();
         entries.insert(thread.tid, thread);
         self.count.fetch_add(1, Ordering::Relaxed);
     }
 
-    fn get(&self, tid: ThreadId) -> Option<Arc<Thread>> {
+    fn get(&self, tid: ThreadId) -> Option<ThreadRef> {
         let entries = self.entries.lock();
         entries.get(&tid).cloned()
     }
 
+    fn find_by_koid(&self, koid: Koid) -> Option<ThreadRef> {
+        let entries = self.entries.lock();
+        entries.values().find(|thread| thread.koid == koid).cloned()
+    }
+
     fn remove(&self, tid: ThreadId) {
         let mut entries = self.entries.lock();
         if entries.remove(&tid).is_some() {
@@ -700,7 +863,7 @@ impl ThreadRegistry {
     }
 }
 
-// SAFETY: ThreadRegistry uses atomic operations and contains Arc which is thread-safe
+// SAFETY: ThreadRegistry uses atomic operations and contains Arcs, which are thread-safe
 unsafe impl Send for ThreadRegistry {}
 unsafe impl Sync for ThreadRegistry {}
 
@@ -757,7 +920,7 @@ pub fn current_thread_id() -> ThreadId {
 /// Get the current thread
 ///
 /// Returns a reference to the current thread.
-pub fn get_current_thread() -> Option<Arc<Thread>> {
+pub fn get_current_thread() -> Option<ThreadRef> {
     let tid = current_thread_id();
     if tid == TID_INVALID {
         return None;
@@ -765,13 +928,34 @@ pub fn get_current_thread() -> Option<Arc<Thread>> {
     THREAD_REGISTRY.get(tid)
 }
 
+/// Get the current thread's process ID without blocking
+///
+/// For interrupt context: returns `None` if the thread registry or the
+/// thread's process is locked by the interrupted code.
+pub fn try_current_pid() -> Option<u64> {
+    let tid = current_thread_id();
+    if tid == TID_INVALID {
+        return None;
+    }
+    let thread = THREAD_REGISTRY.entries.try_lock()?.get(&tid).cloned()?;
+    let pid = *thread.pid.try_lock()?;
+    pid
+}
+
 /// Get a thread by ID
-pub fn get_thread_by_id(tid: ThreadId) -> Option<Arc<Thread>> {
+pub fn get_thread_by_id(tid: ThreadId) -> Option<ThreadRef> {
     THREAD_REGISTRY.get(tid)
 }
 
+/// Look up a thread by koid
+///
+/// Walks every thread, so this is for debuggers rather than hot paths.
+pub fn get_thread_by_koid(koid: Koid) -> Option<ThreadRef> {
+    THREAD_REGISTRY.find_by_koid(koid)
+}
+
 /// Register a thread in the global registry
-pub fn register_thread(thread: Arc<Thread>) {
+pub fn register_thread(thread: ThreadRef) {
     THREAD_REGISTRY.insert(thread);
 }
 
@@ -785,6 +969,52 @@ pub fn thread_count() -> usize {
     THREAD_REGISTRY.count()
 }
 
+/// Call `f` on every registered thread
+pub fn for_each_thread(mut f: impl FnMut(&Thread)) {
+    THREAD_REGISTRY.entries.lock().values().for_each(|thread| f(thread));
+}
+
+/// Call `f` on every registered thread without blocking
+///
+/// Returns false, having visited nothing, if the registry lock is held.
+/// For code that can't wait for the lock holder, like a debugger stopped
+/// in an exception handler.
+pub fn try_for_each_thread(mut f: impl FnMut(&Thread)) -> bool {
+    match THREAD_REGISTRY.entries.try_lock() {
+        Some(entries) => {
+            entries.values().for_each(|thread| f(thread));
+            true
+        }
+        None => false,
+    }
+}
+
+/// `threads` console command
+fn cmd_threads(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    crate::println!(
+        "{:>6} {:>6} {:<10} {:>4} {:>4} {:>10} {:>8}  {}",
+        "tid", "pid", "state", "pri", "refs", "cpu ms", "switches", "name"
+    );
+    for thread in THREAD_REGISTRY.entries.lock().values() {
+        let runtime = thread.runtime();
+        crate::println!(
+            "{:>6} {:>6} {:<10} {:>4} {:>4} {:>10} {:>8}  {}",
+            thread.tid(),
+            thread.pid().unwrap_or(0),
+            alloc::format!("{:?}", thread.state()),
+            thread.priority(),
+            thread.ref_count(),
+            runtime.cpu_time / 1_000_000,
+            runtime.context_switches,
+            thread.name().unwrap_or("")
+        );
+    }
+    crate::println!("{} threads", thread_count());
+    0
+}
+
+crate::static_command!("threads", "list kernel and user threads", cmd_threads);
+
 /// Block the current thread
 ///
 /// # Arguments
@@ -795,7 +1025,9 @@ pub fn thread_count() -> usize {
 ///
 /// This function blocks the current thread. It should be called when
 /// the thread needs to wait for an event (I/O completion, signal, etc.).
+#[track_caller]
 pub fn block_current_thread(reason: BlockReason) {
+    crate::kernel::preempt::check_schedule();
     if let Some(thread) = get_current_thread() {
         thread.block(reason);
         // TODO: Invoke scheduler to switch to another thread
@@ -953,9 +1185,73 @@ pub fn init() {
 /// Kernel stack type (LK compatibility)
 pub type kstack_t = *mut u8;
 
-/// Preempt current thread (LK compatibility stub)
+/// Preempt current thread (LK compatibility)
+///
+/// Called on the outermost interrupt exit when a preemption is pending,
+/// or when preemption is re-enabled with one deferred. Requeues the
+/// current thread and switches to the one the scheduler picks; returns
+/// when the current thread is switched back in.
+#[track_caller]
 pub fn thread_preempt() {
-    // TODO: Implement thread preemption
+    crate::kernel::preempt::check_schedule();
+    run_next(crate::kernel::sched::preempt);
+}
+
+/// Run another thread while the current one is blocked
+///
+/// Switches to the thread the scheduler picks; returns once the current
+/// thread is woken and switched back in, or at once if no switch could
+/// be made.
+#[track_caller]
+pub fn reschedule() {
+    crate::kernel::preempt::check_schedule();
+    run_next(crate::kernel::sched::schedule);
+}
+
+/// Switch to the thread `pick` chooses to replace the current one
+fn run_next(pick: fn() -> Option<ThreadId>) {
+    let state = crate::kernel::arch::arch_interrupt_save();
+    let prev = crate::kernel::sched::current();
+    let next = pick();
+    if let (Some(prev), Some(next)) = (prev, next) {
+        if prev != next && !switch_to(prev, next) {
+            crate::kernel::sched::cancel_switch(prev, next);
+        }
+    }
+    unsafe { crate::kernel::arch::arch_interrupt_restore(state) };
+}
+
+/// Switch this CPU from thread `prev` to thread `next`
+///
+/// Returns true once a later switch comes back to `prev`, or false
+/// without switching if either thread is unknown or `next` has no saved
+/// context, as for a thread created without a kernel stack. Only x86
+/// switches; elsewhere this always returns false. Interrupts must be
+/// disabled.
+fn switch_to(prev: ThreadId, next: ThreadId) -> bool {
+    let (Some(old), Some(new)) = (get_thread_by_id(prev), get_thread_by_id(next)) else {
+        return false;
+    };
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if new.arch.sp == 0 {
+            return false;
+        }
+
+        // The registry keeps both threads alive while they are switched out
+        let old_ptr = ThreadRef::as_ptr(&old) as *mut Thread;
+        let new_ptr = ThreadRef::as_ptr(&new) as *mut Thread;
+        drop((old, new));
+        unsafe { crate::kernel::arch::amd64::arch_context_switch(old_ptr, new_ptr) };
+        true
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (old, new);
+        false
+    }
 }
 
 /// Check if thread is signaled (LK compatibility stub)
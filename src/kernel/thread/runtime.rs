@@ -0,0 +1,187 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Thread Runtime Accounting
+//!
+//! How long each thread has run, how long it waited on a run queue and how
+//! many times it was switched out, for `rx_object_get_info` and `top`.
+//!
+//! # Design
+//!
+//! A thread's [`ThreadRuntime`] is updated at the two edges the scheduler
+//! sees: becoming ready stamps the time it was queued, and a context
+//! switch closes the outgoing thread's running interval and the incoming
+//! thread's queued interval. All fields are relaxed atomics; a reader may
+//! see one interval closed before the other.
+//!
+//! An interval that is still open when the thread is read is counted up to
+//! the time of the read, so a thread that never yields still shows its CPU
+//! time.
+//!
+//! Processes and jobs report the sum over their live threads and processes
+//! plus a [`RuntimeTotals`] that collects what exited ones had used, so
+//! totals never go backwards when a thread exits.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Runtime totals for a thread, process or job
+///
+/// Layout of the `TASK_RUNTIME` info topic.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskRuntimeInfo {
+    /// Time spent running, in nanoseconds
+    pub cpu_time: u64,
+
+    /// Time spent ready to run but waiting for a CPU, in nanoseconds
+    pub queue_time: u64,
+
+    /// Times a thread was switched off a CPU
+    pub context_switches: u64,
+}
+
+impl TaskRuntimeInfo {
+    /// Add `other` into this total
+    pub fn add(&mut self, other: &TaskRuntimeInfo) {
+        self.cpu_time += other.cpu_time;
+        self.queue_time += other.queue_time;
+        self.context_switches += other.context_switches;
+    }
+}
+
+/// A thread's runtime, updated by the scheduler
+pub struct ThreadRuntime {
+    cpu_time: AtomicU64,
+    queue_time: AtomicU64,
+    context_switches: AtomicU64,
+
+    /// When the thread was last made ready, 0 unless it is queued
+    ready_since: AtomicU64,
+
+    /// When the thread was last switched in, 0 unless it is running
+    running_since: AtomicU64,
+}
+
+impl ThreadRuntime {
+    /// A thread that has never run
+    pub const fn new() -> Self {
+        Self {
+            cpu_time: AtomicU64::new(0),
+            queue_time: AtomicU64::new(0),
+            context_switches: AtomicU64::new(0),
+            ready_since: AtomicU64::new(0),
+            running_since: AtomicU64::new(0),
+        }
+    }
+
+    /// The thread was put on a run queue at `now`
+    pub fn mark_ready(&self, now: u64) {
+        // A thread made ready twice keeps its first queue time
+        let _ = self.ready_since.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// The thread was switched onto a CPU at `now`
+    pub fn switch_in(&self, now: u64) {
+        let since = self.ready_since.swap(0, Ordering::Relaxed);
+        if since != 0 {
+            self.queue_time.fetch_add(now.saturating_sub(since), Ordering::Relaxed);
+        }
+        self.running_since.store(now, Ordering::Relaxed);
+    }
+
+    /// The thread was switched off its CPU at `now`
+    pub fn switch_out(&self, now: u64) {
+        let since = self.running_since.swap(0, Ordering::Relaxed);
+        if since != 0 {
+            self.cpu_time.fetch_add(now.saturating_sub(since), Ordering::Relaxed);
+        }
+        self.context_switches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Totals as of `now`, including the interval in progress
+    pub fn snapshot(&self, now: u64) -> TaskRuntimeInfo {
+        let open = |since: &AtomicU64| match since.load(Ordering::Relaxed) {
+            0 => 0,
+            since => now.saturating_sub(since),
+        };
+
+        TaskRuntimeInfo {
+            cpu_time: self.cpu_time.load(Ordering::Relaxed) + open(&self.running_since),
+            queue_time: self.queue_time.load(Ordering::Relaxed) + open(&self.ready_since),
+            context_switches: self.context_switches.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Runtime collected from exited threads or processes
+pub struct RuntimeTotals {
+    cpu_time: AtomicU64,
+    queue_time: AtomicU64,
+    context_switches: AtomicU64,
+}
+
+impl RuntimeTotals {
+    /// Nothing collected yet
+    pub const fn new() -> Self {
+        Self {
+            cpu_time: AtomicU64::new(0),
+            queue_time: AtomicU64::new(0),
+            context_switches: AtomicU64::new(0),
+        }
+    }
+
+    /// Fold in the final totals of an exited thread or process
+    pub fn add(&self, info: &TaskRuntimeInfo) {
+        self.cpu_time.fetch_add(info.cpu_time, Ordering::Relaxed);
+        self.queue_time.fetch_add(info.queue_time, Ordering::Relaxed);
+        self.context_switches.fetch_add(info.context_switches, Ordering::Relaxed);
+    }
+
+    /// Everything collected so far
+    pub fn get(&self) -> TaskRuntimeInfo {
+        TaskRuntimeInfo {
+            cpu_time: self.cpu_time.load(Ordering::Relaxed),
+            queue_time: self.queue_time.load(Ordering::Relaxed),
+            context_switches: self.context_switches.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_runtime() {
+        let runtime = ThreadRuntime::new();
+        assert_eq!(runtime.snapshot(100), TaskRuntimeInfo::default());
+
+        // Queued at 100, runs 150..400, queued again at 400 and 420
+        runtime.mark_ready(100);
+        runtime.switch_in(150);
+        assert_eq!(runtime.snapshot(250).cpu_time, 100);
+        runtime.switch_out(400);
+        runtime.mark_ready(400);
+        runtime.mark_ready(420);
+
+        let info = runtime.snapshot(500);
+        assert_eq!(info.cpu_time, 250);
+        assert_eq!(info.queue_time, 50 + 100);
+        assert_eq!(info.context_switches, 1);
+    }
+
+    #[test]
+    fn test_totals() {
+        let totals = RuntimeTotals::new();
+        let info = TaskRuntimeInfo { cpu_time: 10, queue_time: 20, context_switches: 3 };
+        totals.add(&info);
+        totals.add(&info);
+
+        let mut expected = info;
+        expected.add(&info);
+        assert_eq!(totals.get(), expected);
+    }
+}
@@ -15,6 +15,9 @@
 //! - **Efficient ordering**: Timers stored in priority queue
 //! - **Per-CPU timer queues**: Each CPU has its own timer queue
 //! - **Integration with scheduler**: Thread wakeups integrated
+//! - **Deferred expiry**: The timer interrupt only checks the earliest
+//!   deadline and queues a DPC; callbacks run from the DPC, out of hard
+//!   interrupt context
 //!
 //! # Usage
 //!
@@ -29,6 +32,8 @@
 //! ```
 
 
+use crate::kernel::dpc::{self, Dpc};
+use crate::kernel::lib::timepage;
 use crate::kernel::thread::ThreadId;
 use crate::rustux::types::*;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
@@ -203,7 +208,7 @@ impl Timer {
         // Wake the thread if set
         if let Some(tid) = *self.thread.lock() {
             log_debug!("Waking thread {} due to timer", tid);
-            // TODO: crate::kernel::sched::wake(tid);
+            crate::kernel::sched::wake(tid);
         }
 
         // If periodic, reschedule
@@ -303,18 +308,59 @@ pub fn next_deadline() -> u64 {
     }
 }
 
+/// Fires expired timers out of interrupt context
+static TIMER_DPC: Dpc = Dpc::with_callback(timer_dpc);
+
+unsafe fn timer_dpc(_dpc: &Dpc) {
+    fire_expired(current_time());
+}
+
 /// Process pending timers
 ///
-/// Should be called from timer interrupt handler.
-/// Fires all timers whose deadline has passed.
+/// Should be called from timer interrupt handler. If a deadline has
+/// passed, queues the timer DPC to fire the expired timers.
 pub fn timer_tick(current_time: u64) {
-    // In a real implementation, this would:
-    // 1. Lock the timer queue
-    // 2. Pop all timers with deadline <= current_time
-    // 3. Fire each timer
-    // 4. Re-queue periodic timers
-
     log_trace!("Timer tick: time={}", current_time);
+
+    crate::kernel::lib::watchdog::check(current_time);
+
+    if next_deadline() <= current_time {
+        let _ = dpc::dpc_queue(&TIMER_DPC, false);
+    }
+}
+
+/// Fire every timer whose deadline is at or before `now`
+///
+/// Periodic timers are queued again for their next deadline. Returns the
+/// number of timers fired.
+pub fn fire_expired(now: u64) -> usize {
+    let mut fired = 0;
+    loop {
+        // Pop under the lock, fire without it: callbacks may set timers
+        let entry = {
+            let mut queue = unsafe { TIMER_QUEUE.lock() };
+            match queue.peek() {
+                Some(entry) if entry.deadline <= now => queue.pop(),
+                _ => None,
+            }
+        };
+        let Some(entry) = entry else {
+            break;
+        };
+
+        let timer = unsafe { &*entry.timer };
+        if !timer.is_active() || timer.slot.load(Ordering::Acquire) != entry.id {
+            continue;
+        }
+
+        timer.fire();
+        fired += 1;
+
+        if timer.is_periodic() && timer.is_active() {
+            insert_timer(timer);
+        }
+    }
+    fired
 }
 
 /// Insert a timer into the global queue
@@ -344,22 +390,26 @@ pub fn insert_timer(timer: &Timer) {
 /// * `timer` - Timer to remove
 pub fn remove_timer(timer: &Timer) {
     let slot = timer.slot.load(Ordering::Acquire);
-
-    // In a real implementation, this would search and remove
-    // For now, just mark as inactive
     timer.active.store(false, Ordering::Release);
 
-    let _ = slot;
+    // Drop the entry so the queue never points at a freed timer
+    unsafe {
+        TIMER_QUEUE.lock().retain(|entry| entry.id != slot);
+    }
 }
 
 /// ============================================================================
 /// Current Time
 /// ============================================================================
 
-/// Get the current time in nanoseconds
-///
-/// Returns the monotonic time since boot.
-pub fn current_time() -> u64 {
+/// Added to the hardware clock so time never goes backwards across suspend
+static CLOCK_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Total time spent suspended, in nanoseconds
+static SUSPENDED_TIME: AtomicU64 = AtomicU64::new(0);
+
+/// Read the hardware clock
+fn hw_time() -> u64 {
     #[cfg(target_arch = "aarch64")]
     {
         crate::kernel::arch::arm64::timer::arm64_current_time()
@@ -372,10 +422,48 @@ pub fn current_time() -> u64 {
 
     #[cfg(target_arch = "riscv64")]
     {
-        0 // Stub
+        crate::arch::riscv64::timer::riscv_current_time_ns()
     }
 }
 
+/// Get the current time in nanoseconds
+///
+/// Returns the monotonic time since boot, including time spent suspended.
+pub fn current_time() -> u64 {
+    hw_time() + CLOCK_OFFSET.load(Ordering::Acquire)
+}
+
+/// Total time spent suspended, in nanoseconds
+pub fn suspended_time() -> u64 {
+    SUSPENDED_TIME.load(Ordering::Relaxed)
+}
+
+/// Offset that puts a clock reading `now` at `expected` or later
+fn resync_offset(offset: u64, now: u64, expected: u64) -> u64 {
+    offset + expected.saturating_sub(now)
+}
+
+/// Re-synchronize the clock after a system suspend
+///
+/// `suspended_at` is `current_time()` just before suspending and `slept`
+/// how long the platform slept, as measured by a clock that kept running.
+/// If the hardware clock stopped or reset while suspended, the offset is
+/// advanced so the monotonic time carries on from `suspended_at + slept`.
+/// Timers whose deadlines passed while suspended are then fired.
+pub fn resume(suspended_at: u64, slept: u64) {
+    let expected = suspended_at + slept;
+    let offset = CLOCK_OFFSET.load(Ordering::Acquire);
+    let now = hw_time() + offset;
+    CLOCK_OFFSET.store(resync_offset(offset, now, expected), Ordering::Release);
+
+    let now = current_time();
+    SUSPENDED_TIME.fetch_add(now.saturating_sub(suspended_at), Ordering::Relaxed);
+    timepage::publish_reference(CLOCK_OFFSET.load(Ordering::Acquire), suspended_time());
+    log_debug!("Timer resume: suspended {} ns", now.saturating_sub(suspended_at));
+
+    timer_tick(now);
+}
+
 /// Convert nanoseconds to microseconds
 pub const fn ns_to_us(ns: u64) -> u64 {
     ns / 1000
@@ -464,4 +552,14 @@ mod tests {
         assert_eq!(ms_to_ns(1), 1_000_000);
         assert_eq!(s_to_ns(1), 1_000_000_000);
     }
+
+    #[test]
+    fn test_resync_offset() {
+        // The clock kept running: nothing to add
+        assert_eq!(resync_offset(0, 5_000, 4_000), 0);
+        assert_eq!(resync_offset(7, 5_000, 5_000), 7);
+
+        // The clock reset to 100 after suspending at 4_000 for 1_000
+        assert_eq!(resync_offset(0, 100, 5_000), 4_900);
+    }
 }
@@ -16,6 +16,8 @@
 //! - **Fault isolation**: Page faults on user access are caught and handled
 //! - **Precise reporting**: Exact address and reason of access violation reported
 //! - **No kernel dereference**: Kernel pointers never passed to user functions
+//! - **Closed by default**: SMAP (x86_64) and PAN (arm64) block kernel access
+//!   to user pages except inside a [`UserAccessWindow`]
 //!
 //! # Safety
 //!
@@ -87,6 +89,45 @@ pub type UserMutPtr<T> = UserPtr<T>;
 /// User string pointer
 pub type UserStrPtr = UserPtr<u8>;
 
+/// ============================================================================
+/// User Access Window
+/// ============================================================================
+
+/// RAII guard that lets the kernel touch user pages
+///
+/// Opening the window sets RFLAGS.AC on x86_64 (when SMAP is enabled) or
+/// clears PSTATE.PAN on arm64 (when PAN is implemented). Dropping the guard
+/// closes it again. Keep the window as small as possible: only the actual
+/// load/store of user memory belongs inside it.
+pub struct UserAccessWindow {
+    _private: (),
+}
+
+impl UserAccessWindow {
+    /// Open the user access window
+    #[inline]
+    pub fn open() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        crate::kernel::arch::amd64::mmu::x86_user_access_begin();
+
+        #[cfg(target_arch = "aarch64")]
+        crate::kernel::arch::arm64::mmu::arm64_user_access_begin();
+
+        Self { _private: () }
+    }
+}
+
+impl Drop for UserAccessWindow {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(target_arch = "x86_64")]
+        crate::kernel::arch::amd64::mmu::x86_user_access_end();
+
+        #[cfg(target_arch = "aarch64")]
+        crate::kernel::arch::arm64::mmu::arm64_user_access_end();
+    }
+}
+
 /// ============================================================================
 /// Copy Operations
 /// ============================================================================
@@ -136,7 +177,10 @@ pub unsafe fn copy_from_user(dst: *mut u8, src: UserPtr<u8>, len: usize) -> Resu
     // Perform the copy
     // In a real implementation, this would use a special exception handler
     // to catch page faults during the copy
-    core::ptr::copy_nonoverlapping(src.addr() as *const u8, dst, len);
+    {
+        let _window = UserAccessWindow::open();
+        core::ptr::copy_nonoverlapping(src.addr() as *const u8, dst, len);
+    }
 
     log_trace!(
         "copy_from_user: dst={:#x} src={:#x} len={}",
@@ -193,7 +237,10 @@ pub unsafe fn copy_to_user(dst: UserPtr<u8>, src: *const u8, len: usize) -> Resu
     // Perform the copy
     // In a real implementation, this would use a special exception handler
     // to catch page faults during the copy
-    core::ptr::copy_nonoverlapping(src, dst.addr() as *mut u8, len);
+    {
+        let _window = UserAccessWindow::open();
+        core::ptr::copy_nonoverlapping(src, dst.addr() as *mut u8, len);
+    }
 
     log_trace!(
         "copy_to_user: dst={:#x} src={:#x} len={}",
@@ -236,12 +283,15 @@ pub unsafe fn copy_string_from_user(
 
     // Find the string length by looking for null terminator
     let mut len = 0;
-    while len < max_len {
-        let byte = *((src.addr() + len) as *const u8);
-        if byte == 0 {
-            break;
+    {
+        let _window = UserAccessWindow::open();
+        while len < max_len && is_user_vaddr(src.addr() + len) {
+            let byte = *((src.addr() + len) as *const u8);
+            if byte == 0 {
+                break;
+            }
+            len += 1;
         }
-        len += 1;
     }
 
     // Copy including null terminator
@@ -510,6 +560,12 @@ pub fn init() {
     log_info!("User/kernel boundary safety initialized");
     log_info!("  User address validation: enabled");
     log_info!("  Fault isolation: enabled");
+
+    #[cfg(target_arch = "x86_64")]
+    log_info!("  SMAP: {}", crate::kernel::arch::amd64::mmu::x86_smap_enabled());
+
+    #[cfg(target_arch = "aarch64")]
+    log_info!("  PAN: {}", crate::kernel::arch::arm64::mmu::arm64_pan_enabled());
 }
 
 // ============================================================================
@@ -546,6 +602,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_kernel_pointers_rejected() {
+        let mut buf = [0u8; 8];
+
+        #[cfg(target_arch = "x86_64")]
+        let kernel_addr = 0xffff_8000_0000_1000usize;
+        #[cfg(not(target_arch = "x86_64"))]
+        let kernel_addr = 0xffff_0000_0000_1000usize;
+
+        let kernel_ptr = UserPtr::<u8>::new(kernel_addr);
+        unsafe {
+            assert_eq!(copy_from_user(buf.as_mut_ptr(), kernel_ptr, buf.len()), Err(VmError::InvalidAddress));
+            assert_eq!(copy_to_user(kernel_ptr, buf.as_ptr(), buf.len()), Err(VmError::InvalidAddress));
+            assert_eq!(copy_string_from_user(buf.as_mut_ptr(), kernel_ptr, buf.len()), Err(VmError::InvalidAddress));
+        }
+    }
+
     #[test]
     fn test_user_buffer_validation() {
         #[cfg(target_arch = "x86_64")]
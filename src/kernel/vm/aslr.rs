@@ -0,0 +1,230 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Address Space Layout Randomization
+//!
+//! # Kernel
+//!
+//! - The loader places the kernel image at a random 2MB-aligned address
+//!   and reports how far it moved in the handoff (`kernel_slide`). The
+//!   kernel heap lives in the image, so it moves with it.
+//! - The physmap window is slid by a random multiple of 1GB here, before
+//!   the kernel page tables are built.
+//!
+//! # Userspace
+//!
+//! Every user address space gets a `UserLayout`: randomized stack top,
+//! heap base, vDSO base and default VMAR allocation offset. Each is
+//! moved by up to an eighth of the address space (capped per region),
+//! page granular.
+//!
+//! # Command Line
+//!
+//! `kernel.aslr=false` turns all of it off. The loader reads the same
+//! switch, so the kernel base stays fixed as well.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::kernel::vm::layout::*;
+
+// Import logging macros
+use crate::log_info;
+
+/// Command line switch
+pub const CMDLINE_KEY: &str = "kernel.aslr";
+
+/// Physmap slide granularity (keeps 1GB mappings usable)
+const PHYSMAP_ALIGN: usize = 1 << 30;
+
+/// Range the physmap base may be slid over
+///
+/// arm64 and riscv64 place the physmap at the top of the kernel address
+/// space, leaving no room to slide it.
+#[cfg(target_arch = "x86_64")]
+const PHYSMAP_SLIDE_RANGE: usize = 16 << 40; // 16 TB, below the MMIO window
+
+#[cfg(not(target_arch = "x86_64"))]
+const PHYSMAP_SLIDE_RANGE: usize = 0;
+
+/// Largest stack top offset
+pub const STACK_RANDOM_RANGE: usize = 16 << 30; // 16 GB
+
+/// Largest heap base offset
+pub const HEAP_RANDOM_RANGE: usize = 1 << 30; // 1 GB
+
+/// Largest vDSO base offset
+pub const VDSO_RANDOM_RANGE: usize = 1 << 40; // 1 TB
+
+/// Largest default VMAR allocation offset
+pub const MMAP_RANDOM_RANGE: usize = 1 << 40; // 1 TB
+
+/// Whether randomization is on
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether `init` has run
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Physmap offset from the architecture's base
+static PHYSMAP_SLIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// Read the command line switch and pick the physmap slide
+///
+/// Must run before the physmap is mapped; `vm::init` calls it first.
+pub fn init() {
+    if INITIALIZED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    let enabled = crate::kernel::cmdline::cmdline_get_bool(CMDLINE_KEY, true);
+    ENABLED.store(enabled, Ordering::Release);
+    if !enabled {
+        log_info!("ASLR: disabled");
+        return;
+    }
+
+    PHYSMAP_SLIDE.store(random_offset(PHYSMAP_SLIDE_RANGE, PHYSMAP_ALIGN), Ordering::Release);
+    log_info!("ASLR: kernel slide {:#x}, physmap at {:#x}", kernel_slide(), physmap_base());
+}
+
+/// Whether address space layout randomization is on
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Distance the loader moved the kernel image from its linked base
+pub fn kernel_slide() -> u64 {
+    crate::kernel::handoff::get().map_or(0, |handoff| handoff.kernel_slide)
+}
+
+/// Base of the physmap window
+pub fn physmap_base() -> VAddr {
+    #[cfg(target_arch = "aarch64")]
+    let base = arm64::KERNEL_PHYSMAP_BASE;
+
+    #[cfg(target_arch = "x86_64")]
+    let base = amd64::KERNEL_PHYSMAP_BASE;
+
+    #[cfg(target_arch = "riscv64")]
+    let base = riscv::KERNEL_PHYSMAP_BASE;
+
+    base + PHYSMAP_SLIDE.load(Ordering::Acquire)
+}
+
+/// Random multiple of `align` below `range`
+///
+/// Returns 0 if randomization is off or `range` is smaller than `align`.
+pub fn random_offset(range: usize, align: usize) -> usize {
+    if !is_enabled() || align == 0 || range < align {
+        return 0;
+    }
+    let slots = (range / align) as u64;
+    crate::kernel::lib::crypto::rand_int(slots) as usize * align
+}
+
+/// ============================================================================
+/// User Layout
+/// ============================================================================
+
+/// Where a user address space places its standard regions
+///
+/// These are starting points: the stack is mapped below `stack_top`, and
+/// the heap, vDSO and VMAR allocations search upwards from their bases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserLayout {
+    /// Top of the initial thread's stack
+    pub stack_top: VAddr,
+
+    /// Start of the program break heap
+    pub heap_base: VAddr,
+
+    /// Where the vDSO is mapped
+    pub vdso_base: VAddr,
+
+    /// Where default (non-SPECIFIC) VMAR allocations start
+    pub mmap_base: VAddr,
+}
+
+impl UserLayout {
+    /// Randomized layout for `[base, top)`
+    ///
+    /// Same as `fixed` when randomization is off.
+    pub fn new(base: VAddr, top: VAddr) -> Self {
+        let range = |cap: usize| cap.min(top.saturating_sub(base) / 8);
+        Self::with_offsets(
+            base,
+            top,
+            [
+                random_offset(range(STACK_RANDOM_RANGE), PAGE_SIZE),
+                random_offset(range(HEAP_RANDOM_RANGE), PAGE_SIZE),
+                random_offset(range(VDSO_RANDOM_RANGE), PAGE_SIZE),
+                random_offset(range(MMAP_RANDOM_RANGE), PAGE_SIZE),
+            ],
+        )
+    }
+
+    /// Deterministic layout for `[base, top)`
+    pub fn fixed(base: VAddr, top: VAddr) -> Self {
+        Self::with_offsets(base, top, [0; 4])
+    }
+
+    /// Layout with explicit stack, heap, vDSO and mmap offsets
+    ///
+    /// Regions start at fixed fractions of the span: VMAR allocations at
+    /// the bottom, the heap a quarter of the way up, the vDSO halfway,
+    /// and the stack at the top. Offsets are at most an eighth of the
+    /// span, so the randomized regions never cross.
+    fn with_offsets(base: VAddr, top: VAddr, offsets: [usize; 4]) -> Self {
+        let base = page_align_up(base);
+        let top = page_align_down(top);
+        let span = top.saturating_sub(base);
+        let [stack, heap, vdso, mmap] = offsets;
+
+        Self {
+            stack_top: top - stack,
+            heap_base: base + span / 4 + heap,
+            vdso_base: base + span / 2 + vdso,
+            mmap_base: base + mmap,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_layout() {
+        let layout = UserLayout::fixed(0x1000, 0x8000_0000);
+        assert_eq!(layout.stack_top, 0x8000_0000);
+        assert_eq!(layout.mmap_base, 0x1000);
+        assert_eq!(layout.heap_base, 0x1000 + (0x8000_0000 - 0x1000) / 4);
+        assert_eq!(layout.vdso_base, 0x1000 + (0x8000_0000 - 0x1000) / 2);
+
+        // Unaligned bounds are rounded inwards
+        let layout = UserLayout::fixed(0x1234, 0x7fff_ffff);
+        assert_eq!(layout.mmap_base, 0x2000);
+        assert_eq!(layout.stack_top, 0x7fff_f000);
+    }
+
+    #[test]
+    fn test_regions_do_not_cross() {
+        let (base, top) = (0x1000usize, 0x0000_7FFF_FFFF_F000usize);
+        let max = (top - base) / 8;
+        let layout = UserLayout::with_offsets(base, top, [max, max, max, max]);
+        assert!(layout.mmap_base < layout.heap_base);
+        assert!(layout.heap_base < layout.vdso_base);
+        assert!(layout.vdso_base < layout.stack_top);
+        assert!(layout.stack_top <= top);
+    }
+
+    #[test]
+    fn test_random_offset_disabled() {
+        // `init` has not run, so randomization is off
+        assert!(!is_enabled());
+        assert_eq!(random_offset(1 << 40, PAGE_SIZE), 0);
+        assert_eq!(UserLayout::new(0x1000, 0x8000_0000), UserLayout::fixed(0x1000, 0x8000_0000));
+    }
+}
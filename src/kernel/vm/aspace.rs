@@ -21,6 +21,8 @@
 //! Address spaces use interior mutability with mutexes to allow safe concurrent access.
 
 
+use crate::kernel::object::vmo::CachePolicy;
+use crate::kernel::vm::aslr::UserLayout;
 use crate::kernel::vm::layout::*;
 use crate::kernel::vm::page_table::*;
 use crate::kernel::vm::{VmError, Result};
@@ -218,6 +220,9 @@ pub struct AddressSpace {
     /// Size in bytes
     size: usize,
 
+    /// Stack, heap, vDSO and mmap placement (randomized for user spaces)
+    layout: UserLayout,
+
     /// Reference count
     ref_count: AtomicU64,
 }
@@ -245,12 +250,19 @@ impl AddressSpace {
         // Allocate ASID
         let asid = ASID_ALLOCATOR.allocate();
 
+        let layout = if flags.is_user() {
+            UserLayout::new(base, base + size)
+        } else {
+            UserLayout::fixed(base, base + size)
+        };
+
         let mut aspace = Self {
             page_table: Mutex::new(page_table),
             flags,
             asid,
             base,
             size,
+            layout,
             ref_count: AtomicU64::new(1),
         };
 
@@ -303,6 +315,11 @@ impl AddressSpace {
         self.base
     }
 
+    /// Get the region placement for this address space
+    pub fn layout(&self) -> &UserLayout {
+        &self.layout
+    }
+
     /// Get the size in bytes
     pub fn size(&self) -> usize {
         self.size
@@ -330,6 +347,21 @@ impl AddressSpace {
         paddr: PAddr,
         count: usize,
         prot: MemProt,
+    ) -> Result {
+        self.map_with_cache_policy(vaddr, paddr, count, prot, CachePolicy::Default)
+    }
+
+    /// Map pages into this address space with a cache policy
+    ///
+    /// Device registers are mapped `Uncached`, framebuffers
+    /// `WriteCombining`.
+    pub fn map_with_cache_policy(
+        &self,
+        vaddr: VAddr,
+        paddr: PAddr,
+        count: usize,
+        prot: MemProt,
+        cache_policy: CachePolicy,
     ) -> Result {
         if !self.is_valid_vaddr(vaddr) {
             return Err(VmError::InvalidAddress);
@@ -341,7 +373,7 @@ impl AddressSpace {
             return Err(VmError::InvalidAddress);
         }
 
-        let flags = PageTableFlags::from_prot(prot);
+        let flags = PageTableFlags::from_prot(prot) | PageTableFlags::from_cache_policy(cache_policy);
 
         // Set user flag if this is a user address space
         let flags = if self.is_user() {
@@ -364,11 +396,23 @@ impl AddressSpace {
 
     /// Change protection for pages in this address space
     pub fn protect(&self, vaddr: VAddr, count: usize, prot: MemProt) -> Result {
+        self.protect_with_cache_policy(vaddr, count, prot, CachePolicy::Default)
+    }
+
+    /// Change protection for pages in this address space, keeping the
+    /// cache policy they were mapped with
+    pub fn protect_with_cache_policy(
+        &self,
+        vaddr: VAddr,
+        count: usize,
+        prot: MemProt,
+        cache_policy: CachePolicy,
+    ) -> Result {
         if !self.is_valid_vaddr(vaddr) {
             return Err(VmError::InvalidAddress);
         }
 
-        let flags = PageTableFlags::from_prot(prot);
+        let flags = PageTableFlags::from_prot(prot) | PageTableFlags::from_cache_policy(cache_policy);
 
         // Set user flag if this is a user address space
         let flags = if self.is_user() {
@@ -389,6 +433,18 @@ impl AddressSpace {
         self.page_table.lock().resolve(vaddr)
     }
 
+    /// Harvest the accessed/dirty bits of `count` pages at `vaddr`
+    ///
+    /// See [`PageTable::harvest_pages`].
+    pub fn harvest(&self, vaddr: VAddr, count: usize, clear: bool,
+                   f: impl FnMut(PAddr, PageUsage)) -> Result {
+        if !self.is_valid_vaddr(vaddr) {
+            return Err(VmError::InvalidArgs);
+        }
+
+        self.page_table.lock().harvest_pages(vaddr, count, clear, f)
+    }
+
     /// Flush TLB entries for this address space
     pub fn flush_tlb(&self) {
         self.page_table.lock().flush_tlb();
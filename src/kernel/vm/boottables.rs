@@ -19,6 +19,7 @@
 //! 5. Verifies stability
 
 
+use crate::kernel::object::vmo::CachePolicy;
 use crate::kernel::vm::layout::*;
 
 // Architecture-specific layout imports
@@ -268,14 +269,8 @@ fn setup_kernel_stacks(aspace: &AddressSpace) -> Result {
 
 /// Setup physical memory direct map
 fn setup_physmap(aspace: &AddressSpace) -> Result {
-    #[cfg(target_arch = "aarch64")]
-    let (base, size) = (layout_arch::KERNEL_PHYSMAP_BASE, layout_arch::KERNEL_PHYSMAP_SIZE);
-
-    #[cfg(target_arch = "x86_64")]
-    let (base, size) = (layout_arch::KERNEL_PHYSMAP_BASE, layout_arch::KERNEL_PHYSMAP_SIZE);
-
-    #[cfg(target_arch = "riscv64")]
-    let (base, size) = (layout_arch::KERNEL_PHYSMAP_BASE, layout_arch::KERNEL_PHYSMAP_SIZE);
+    // The base is slid by ASLR; the window size is fixed
+    let (base, size) = (crate::kernel::vm::aslr::physmap_base(), layout_arch::KERNEL_PHYSMAP_SIZE);
 
     // Map the first portion of physical memory 1:1
     let map_size = size.min(1 * 1024 * 1024 * 1024); // Start with 1GB
@@ -314,7 +309,7 @@ fn setup_mmio_region(aspace: &AddressSpace) -> Result {
     let paddr = 0x0900_0000; // Example UART address
 
     // Device memory should be uncached
-    aspace.map(base, paddr, pages, MemProt::ReadWrite)?;
+    aspace.map_with_cache_policy(base, paddr, pages, MemProt::ReadWrite, CachePolicy::Uncached)?;
 
     log_debug!(
         "Mapped MMIO: {:#x} -> {:#x}",
@@ -11,26 +11,34 @@
 //!
 //! # Design
 //!
-//! - **Lazy allocation**: Pages are allocated on first access
-//! - **COW support**: Copy-on-write faults are handled
-//! - **VMAR lookup**: Fault addresses are resolved to VMAR regions
-//! - **VMO integration**: Physical pages are obtained from VMOs
+//! - **VMAR lookup**: Fault addresses are resolved to VMAR mappings;
+//!   faults outside a mapping, or with an access its protection doesn't
+//!   allow, are not handled
+//! - **Lazy allocation**: VMO pages are committed on first write or
+//!   instruction fetch; reads of untouched memory map the shared zero page
+//!   (see [`zero_page`])
+//! - **VMO integration**: Physical pages are obtained from VMOs and mapped
+//!   with the mapping's protection and the VMO's cache policy
+//! - **COW support**: Writes to present pages of COW clones get a copy
 //!
 //! # Page Fault Handling Flow
 //!
 //! ```text
 //! 1. Page fault occurs
-//! 2. Lookup VMAR region for fault address
-//! 3. Find VMO mapping for the region
-//! 4. Allocate/commit page from VMO
+//! 2. Look up the VMAR mapping for the fault address
+//! 3. Check the access against the mapping's protection
+//! 4. Map the zero page, or commit the page from the VMO
 //! 5. Map page into address space
 //! 6. Resume execution
 //! ```
 
 
+use crate::kernel::object::vmo::Vmo;
+use crate::kernel::syscalls::vmar;
 use crate::kernel::vm::aspace::AddressSpace;
 use crate::kernel::vm::page_table::*;
-use crate::kernel::vm::layout::PAGE_SIZE;
+use crate::kernel::vm::layout::{MemProt, PAGE_SIZE};
+use crate::kernel::vm::zero_page;
 use crate::kernel::pmm;
 use crate::rustux::types::*;
 use crate::rustux::types::err::*;
@@ -151,96 +159,122 @@ pub fn handle_page_fault(info: PageFaultInfo, aspace: &Arc<AddressSpace>) -> Pag
     // Align address to page boundary
     let fault_addr = info.addr & !(PAGE_SIZE - 1);
 
-    // Try to find the VMAR region for this address
-    // TODO: Implement VMAR lookup from address space
-    // For now, we'll handle it as a stub
-
-    // Check if this is a COW write fault (write to read-only page)
-    if is_cow_fault(info) {
-        log_debug!("Detected COW write fault");
-        if let Ok(_) = try_cow_allocation(fault_addr, aspace, info) {
-            log_debug!("Page fault handled via COW");
-            return PageFaultResult::Handled;
+    match resolve_fault(fault_addr, aspace, info) {
+        Ok(()) => PageFaultResult::Handled,
+        Err(err) if info.from_user_mode() => {
+            log_debug!("Page fault forwarding to userspace: {}", err);
+            PageFaultResult::UserSpace
         }
-    }
-
-    // Check if this is a lazy allocation fault
-    if info.is_not_present() {
-        // Try to handle as lazy allocation
-        if let Ok(_) = try_lazy_allocation(fault_addr, aspace, info) {
-            log_debug!("Page fault handled via lazy allocation");
-            return PageFaultResult::Handled;
+        Err(err) => {
+            log_error!("Kernel page fault at {:#x} - fatal: {}", info.addr, err);
+            PageFaultResult::Fatal
         }
     }
+}
+
+/// Find the VMAR mapping covering `addr`
+///
+/// Returns the mapped VMO, the VMO offset backing `addr` and the mapping's
+/// protection.
+///
+/// # Errors
+///
+/// - `RX_ERR_NOT_FOUND` - nothing is mapped at `addr`
+fn lookup_mapping(addr: VAddr) -> Result<(Arc<Vmo>, u64, MemProt)> {
+    // All processes share the root user VMAR for now
+    let root = vmar::user_vmar()?;
+    let offset = (addr as u64).checked_sub(root.base).ok_or(RX_ERR_NOT_FOUND)?;
+    root.lookup(offset).ok_or(RX_ERR_NOT_FOUND)
+}
 
-    // If we couldn't handle the fault, decide what to do
-    if info.from_user_mode() {
-        log_debug!("Page fault forwarding to userspace");
-        PageFaultResult::UserSpace
+/// Whether a mapping with `prot` allows the access that faulted
+pub fn access_allowed(info: PageFaultInfo, prot: MemProt) -> bool {
+    if info.is_instruction() {
+        prot.can_execute()
+    } else if info.is_write() {
+        prot.can_write()
     } else {
-        log_error!("Kernel page fault - fatal");
-        PageFaultResult::Fatal
+        prot.can_read()
+    }
+}
+
+/// Resolve a fault at page-aligned `addr` through the VMAR mapping there
+///
+/// # Errors
+///
+/// - `RX_ERR_NOT_FOUND` - nothing is mapped at `addr`
+/// - `RX_ERR_ACCESS_DENIED` - the mapping doesn't allow the access
+/// - errors from committing or mapping the page
+fn resolve_fault(addr: VAddr, aspace: &Arc<AddressSpace>, info: PageFaultInfo) -> Result {
+    let (vmo, vmo_offset, prot) = lookup_mapping(addr)?;
+    if !access_allowed(info, prot) {
+        log_debug!(
+            "Page fault at {:#x} not allowed by mapping protection {:?}",
+            addr, prot
+        );
+        return Err(RX_ERR_ACCESS_DENIED);
+    }
+    let index = vmo_offset as usize / PAGE_SIZE;
+
+    match aspace.resolve(addr) {
+        // The first write or instruction fetch to a page that was only
+        // read so far
+        Some(current) if zero_page::is_zero_page(current as PAddr) => {
+            if !info.is_write() && !info.is_instruction() {
+                return Ok(());
+            }
+            let paddr = vmo.commit_page_paddr(index)?;
+            zero_page::replace(aspace, addr, paddr, prot)?;
+            log_debug!("Zero page at {:#x} replaced with paddr={:#x}", addr, paddr);
+            Ok(())
+        }
+        Some(current) if is_cow_fault(info) && vmo.flags.is_cow() => {
+            try_cow_allocation(addr, aspace, current as PAddr, prot)
+        }
+        // Another CPU mapped the page first
+        Some(_) => Ok(()),
+        None => try_lazy_allocation(addr, aspace, info, &vmo, index, prot),
     }
 }
 
-/// Try to handle a page fault via lazy allocation
+/// Try to handle a not-present fault via lazy allocation
 ///
 /// # Arguments
 ///
 /// * `addr` - Faulting virtual address (page-aligned)
 /// * `aspace` - Address space
 /// * `info` - Page fault info
+/// * `vmo` - VMO mapped at `addr`
+/// * `index` - Index of the VMO page backing `addr`
+/// * `prot` - Protection of the mapping
 ///
 /// # Returns
 ///
 /// * Ok(()) if handled successfully
 /// * Err otherwise
-fn try_lazy_allocation(addr: VAddr, _aspace: &Arc<AddressSpace>, _info: PageFaultInfo) -> Result {
-    log_debug!(
-        "Lazy allocation for addr={:#x}",
-        addr
-    );
-
-    // Lazy allocation steps:
-    //
-    // 1. Lookup VMAR region containing addr
-    //    - In a full implementation, this would walk the VMAR tree
-    //    - Find the region and its VMO mapping
-    //
-    // 2. Check if this is a zero page access
-    //    - If reading from uninitialized data, use zero page optimization
-    //    - Map a shared read-only zero page
-    //
-    // 3. Allocate a new page if needed
-    //    - Use PMM to allocate physical page
-    //    - Zero it or copy data from VMO
-    //
-    // 4. Map page into address space
-    //    - Update page tables
-    //    - Set correct permissions (R/W/X)
-    //
-    // 5. Flush TLB for this page
-
-    // Allocate a new physical page for the lazy allocation
-    let paddr = pmm::pmm_alloc_page(pmm::PMM_ALLOC_FLAG_ANY)
-        .map_err(|_| RX_ERR_NO_MEMORY)?;
-
-    // Zero the page for safety
-    // TODO: For write faults, zero the page
-    // For read faults on committed but not-yet-paged regions, this would be COW
-    unsafe {
-        let dst = crate::kernel::vm::phys_to_physmap(paddr as usize) as *mut u8;
-        core::ptr::write_bytes(dst, 0, PAGE_SIZE);
+fn try_lazy_allocation(
+    addr: VAddr,
+    aspace: &Arc<AddressSpace>,
+    info: PageFaultInfo,
+    vmo: &Vmo,
+    index: usize,
+    prot: MemProt,
+) -> Result {
+    log_debug!("Lazy allocation for addr={:#x} page={}", addr, index);
+
+    // Reads share the zero page until the first write
+    let committed = vmo.pages.get(index).is_some();
+    if !committed && !info.is_write() && !info.is_instruction() && zero_page::map(aspace, addr).is_ok() {
+        log_debug!("Lazy allocation: mapped shared zero page at addr={:#x}", addr);
+        return Ok(());
     }
 
-    log_debug!("Lazy allocation: allocated and zeroed page at paddr={:#x}", paddr);
+    let paddr = vmo.commit_page_paddr(index)?;
+    if let Err(err) = aspace.map_with_cache_policy(addr, paddr as usize, 1, prot, vmo.cache_policy()) {
+        return Err(err.into());
+    }
 
-    // TODO: Map the page into the address space
-    // This requires:
-    // 1. Walking the page tables for addr
-    // 2. Creating/updating the PTE
-    // 3. Setting correct permissions
-    // 4. Invalidating the TLB
+    log_debug!("Lazy allocation: mapped paddr={:#x} prot={:?}", paddr, prot);
 
     // Record that we handled a lazy allocation
     #[cfg(feature = "vm_stats")]
@@ -249,71 +283,36 @@ fn try_lazy_allocation(addr: VAddr, _aspace: &Arc<AddressSpace>, _info: PageFaul
     Ok(())
 }
 
-/// Try to handle a page fault via COW
+/// Try to handle a write fault on a present page of a COW clone
 ///
 /// # Arguments
 ///
 /// * `addr` - Faulting virtual address (page-aligned)
 /// * `aspace` - Address space
-/// * `info` - Page fault info
+/// * `orig_paddr` - Page currently mapped at `addr`
+/// * `prot` - Protection of the mapping
 ///
 /// # Returns
 ///
 /// * Ok(()) if handled successfully
 /// * Err otherwise
-fn try_cow_allocation(addr: VAddr, _aspace: &Arc<AddressSpace>, _info: PageFaultInfo) -> Result {
+fn try_cow_allocation(
+    addr: VAddr,
+    aspace: &Arc<AddressSpace>,
+    orig_paddr: PAddr,
+    prot: MemProt,
+) -> Result {
     log_debug!("COW allocation for addr={:#x}", addr);
 
-    // COW page fault handling steps:
-    //
-    // 1. Find the VMAR region containing this address
-    // 2. Get the VMO and offset for this mapping
-    // 3. Check if the VMO is a COW clone (has parent)
-    // 4. If COW:
-    //    a. Allocate a new physical page
-    //    b. Copy contents from parent's page
-    //    c. Map the new page with write permissions
-    //    d. Update the VMO's page map
-    //    e. Flush TLB for this page
-    // 5. Mark the page as committed in this VMO
-
-    // For now, we'll implement a simplified version that:
-    // - Allocates a new page
-    // - Logs what would happen
-    // - Returns success
-
-    // Allocate a new physical page for the COW copy
-    let new_paddr = match pmm::pmm_alloc_page(pmm::PMM_ALLOC_FLAG_ANY) {
-        Ok(paddr) => paddr,
-        Err(_) => {
-            log_error!("COW: Failed to allocate physical page");
-            return Err(RX_ERR_NO_MEMORY);
-        }
-    };
-
-    log_debug!("COW: Allocated new physical page {:#x}", new_paddr);
-
-    // Get the original page's physical address
-    // In a full implementation, we would:
-    // 1. Walk the page tables to find the current mapping
-    // 2. Get the physical address of the current page
-    // 3. Map both pages temporarily and copy the data
-
-    // Copy page contents
-    // TODO: Implement proper page copy using temporary mapping
-    // For now, we just zero the new page
-    unsafe {
-        let dst = crate::kernel::vm::phys_to_physmap(new_paddr as usize) as *mut u8;
-        core::ptr::write_bytes(dst, 0, PAGE_SIZE);
+    let new_paddr = cow_page_split(addr, orig_paddr)?;
+    let remapped = aspace
+        .unmap(addr, 1)
+        .and_then(|()| aspace.map(addr, new_paddr as usize, 1, prot));
+    if let Err(err) = remapped {
+        pmm::pmm_free_page(new_paddr);
+        return Err(err.into());
     }
-
-    log_debug!("COW: Page copied, would now map with write permissions");
-
-    // In a full implementation, we would now:
-    // 1. Update the page table entry to point to the new page
-    // 2. Add write permission to the PTE
-    // 3. Invalidate the TLB entry for this page
-    // 4. Update the VMO's page map to reflect the new physical page
+    aspace.flush_tlb_va(addr);
 
     // Record COW fault in statistics
     #[cfg(feature = "vm_stats")]
@@ -445,6 +444,115 @@ pub fn init() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::kernel::object::vmo::{CachePolicy, VmoFlags};
+
+    /// Map a two-page VMO with `prot` in the root user VMAR
+    ///
+    /// Returns the VMO and the address it is mapped at.
+    fn map_vmo(prot: MemProt) -> (Arc<Vmo>, VAddr) {
+        if vmar::user_vmar().is_err() {
+            vmar::init_root_user_vmar();
+        }
+        let root = vmar::user_vmar().unwrap();
+        let vmo = Arc::new(Vmo::create(2 * PAGE_SIZE, VmoFlags::empty).unwrap());
+        let offset = root
+            .map(vmo.clone(), 0, 0, 2 * PAGE_SIZE as u64, prot, CachePolicy::Default, 0)
+            .unwrap();
+        (vmo, (root.base + offset) as VAddr)
+    }
+
+    fn fault(aspace: &Arc<AddressSpace>, addr: VAddr, flags: u32) -> PageFaultResult {
+        handle_page_fault(PageFaultInfo::new(addr, flags | PF_FLAG_USER, 0, true), aspace)
+    }
+
+    #[test]
+    fn test_access_allowed() {
+        let read = PageFaultInfo::new(0x1000, PF_FLAG_NOT_PRESENT, 0, true);
+        let write = PageFaultInfo::new(0x1000, PF_FLAG_WRITE, 0, true);
+        let fetch = PageFaultInfo::new(0x1000, PF_FLAG_INSTRUCTION, 0, true);
+
+        assert!(access_allowed(read, MemProt::Read));
+        assert!(!access_allowed(write, MemProt::Read));
+        assert!(access_allowed(write, MemProt::ReadWrite));
+        assert!(!access_allowed(fetch, MemProt::ReadWrite));
+        assert!(access_allowed(fetch, MemProt::Read | MemProt::Execute));
+        assert!(!access_allowed(read, MemProt::None));
+    }
+
+    #[test]
+    fn test_fault_outside_mapping() {
+        let aspace = Arc::new(AddressSpace::new_user().unwrap());
+        let (_vmo, addr) = map_vmo(MemProt::ReadWrite);
+
+        // Just past the end of the mapping
+        let past = addr + 2 * PAGE_SIZE;
+        assert!(matches!(fault(&aspace, past, PF_FLAG_NOT_PRESENT), PageFaultResult::UserSpace));
+        assert!(aspace.resolve(past).is_none());
+
+        // Kernel faults on unmapped memory are fatal
+        let info = PageFaultInfo::new(past, PF_FLAG_NOT_PRESENT, 0, false);
+        assert!(matches!(handle_page_fault(info, &aspace), PageFaultResult::Fatal));
+    }
+
+    #[test]
+    fn test_fault_wrong_access() {
+        let aspace = Arc::new(AddressSpace::new_user().unwrap());
+        let (vmo, addr) = map_vmo(MemProt::Read);
+
+        // Writes and instruction fetches to a read-only mapping
+        let write = PF_FLAG_WRITE | PF_FLAG_NOT_PRESENT;
+        assert!(matches!(fault(&aspace, addr, write), PageFaultResult::UserSpace));
+        let fetch = PF_FLAG_INSTRUCTION | PF_FLAG_NOT_PRESENT;
+        assert!(matches!(fault(&aspace, addr, fetch), PageFaultResult::UserSpace));
+        assert!(aspace.resolve(addr).is_none());
+        assert_eq!(vmo.pages.get(0), None);
+    }
+
+    #[test]
+    fn test_fault_read_maps_zero_page() {
+        let aspace = Arc::new(AddressSpace::new_user().unwrap());
+        let (vmo, addr) = map_vmo(MemProt::Read);
+
+        assert!(matches!(fault(&aspace, addr, PF_FLAG_NOT_PRESENT), PageFaultResult::Handled));
+        if zero_page::paddr().is_some() {
+            assert!(zero_page::is_zero_page(aspace.resolve(addr).unwrap() as PAddr));
+            assert_eq!(vmo.pages.get(0), None);
+        }
+
+        // Still read-only: the zero page isn't broken by a write
+        assert!(matches!(fault(&aspace, addr, PF_FLAG_WRITE), PageFaultResult::UserSpace));
+    }
+
+    #[test]
+    fn test_fault_write_commits_vmo_page() {
+        let aspace = Arc::new(AddressSpace::new_user().unwrap());
+        let (vmo, addr) = map_vmo(MemProt::ReadWrite);
+
+        // Read first, then write: the zero page is replaced by the VMO's page
+        let page = addr + PAGE_SIZE;
+        assert!(matches!(fault(&aspace, page, PF_FLAG_NOT_PRESENT), PageFaultResult::Handled));
+        assert!(matches!(fault(&aspace, page, PF_FLAG_WRITE), PageFaultResult::Handled));
+        let paddr = aspace.resolve(page).unwrap() as PAddr;
+        assert!(!zero_page::is_zero_page(paddr));
+        assert_eq!(vmo.commit_page_paddr(1), Ok(paddr));
+
+        // A write to an untouched page maps the committed page directly
+        assert!(matches!(fault(&aspace, addr, PF_FLAG_WRITE | PF_FLAG_NOT_PRESENT), PageFaultResult::Handled));
+        assert_eq!(aspace.resolve(addr).map(|paddr| paddr as PAddr), vmo.commit_page_paddr(0).ok());
+    }
+
+    #[test]
+    fn test_zero_page_replace_keeps_prot() {
+        let aspace = Arc::new(AddressSpace::new_user().unwrap());
+        let (vmo, addr) = map_vmo(MemProt::Read | MemProt::Execute);
+
+        // Executing a page that was only read maps it read/execute, not
+        // writable
+        assert!(matches!(fault(&aspace, addr, PF_FLAG_NOT_PRESENT), PageFaultResult::Handled));
+        assert!(matches!(fault(&aspace, addr, PF_FLAG_INSTRUCTION), PageFaultResult::Handled));
+        assert!(vmo.pages.get(0).is_some());
+        assert!(matches!(fault(&aspace, addr, PF_FLAG_WRITE), PageFaultResult::UserSpace));
+    }
 
     #[test]
     fn test_page_fault_info() {
@@ -200,6 +200,11 @@ pub mod amd64 {
     pub const KERNEL_HEAP_BASE: VAddr = KERNEL_BASE + 0x0500_0000;
     pub const KERNEL_HEAP_SIZE: usize = 1024 * 1024 * 1024; // 1 GB
 
+    /// EFI runtime services regions, at the addresses given to
+    /// SetVirtualAddressMap
+    pub const KERNEL_EFI_BASE: VAddr = 0xFFFF_FFEF_0000_0000;
+    pub const KERNEL_EFI_SIZE: usize = 64 * 1024 * 1024 * 1024; // 64 GB
+
     /// User address space (lower half)
     pub const USER_BASE: VAddr = 0x0000_0000_0000_0000;
     pub const USER_MAX: VAddr = 0x0000_7FFF_FFFF_FFFF;
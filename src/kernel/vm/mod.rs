@@ -21,12 +21,15 @@
 //! - [`layout`] - Virtual address layout definitions
 //! - [`page_table`] - Cross-architecture page table abstraction
 //! - [`aspace`] - Address space management
+//! - [`aslr`] - Address space layout randomization
+//! - [`pressure`] - Memory pressure levels, reclaim and the OOM killer
 //! - [`vmo`] - Virtual Memory Objects
 
 
 pub mod layout;
 pub mod page_table;
 pub mod aspace;
+pub mod aslr;
 pub mod arch_vm_aspace;
 pub mod boottables;
 pub mod debug;
@@ -36,9 +39,11 @@ pub mod stats;
 pub mod fault;
 pub mod walker;
 pub mod pmm;
+pub mod pressure;
 pub mod physmap;
 pub mod vm_object;
 pub mod init;
+pub mod zero_page;
 
 // Re-exports for convenience
 pub use layout::{
@@ -173,7 +178,18 @@ impl VmError {
 // Convert VmError to Status for use with ? operator
 impl From<VmError> for crate::rustux::types::Status {
     fn from(err: VmError) -> Self {
-        err as i32
+        use crate::rustux::types::err::*;
+        match err {
+            VmError::Ok => crate::rustux::types::status::OK,
+            VmError::InvalidArgs | VmError::InvalidAddress | VmError::AlignmentError => RX_ERR_INVALID_ARGS,
+            VmError::NoMemory => RX_ERR_NO_MEMORY,
+            VmError::AccessDenied | VmError::PermissionDenied => RX_ERR_ACCESS_DENIED,
+            VmError::AlreadyMapped => RX_ERR_ALREADY_EXISTS,
+            VmError::NotMapped | VmError::NotFound => RX_ERR_NOT_FOUND,
+            VmError::PageFault => RX_ERR_IO,
+            VmError::Busy => RX_ERR_SHOULD_WAIT,
+            VmError::BadState => RX_ERR_BAD_STATE,
+        }
     }
 }
 
@@ -184,6 +200,7 @@ pub type Result<T = ()> = core::result::Result<T, VmError>;
 ///
 /// Must be called early in boot to set up the virtual memory subsystem.
 pub fn init() {
+    aslr::init();
     layout::validate_layout();
     page_table::init();
     aspace::init();
@@ -269,6 +286,46 @@ pub trait ArchPageTable: Sized {
     /// Update page table entry flags
     fn protect(&mut self, vaddr: VAddr, flags: PageTableFlags) -> Result;
 
+    /// Map `count` physically contiguous pages
+    ///
+    /// Tables that support large pages override this to use them where the
+    /// run is aligned; the default maps one 4KB page at a time.
+    fn map_range(&mut self, vaddr: VAddr, paddr: PAddr, count: usize, flags: PageTableFlags) -> Result {
+        for i in 0..count {
+            self.map(vaddr + i * PAGE_SIZE, paddr + i * PAGE_SIZE, flags)?;
+        }
+        Ok(())
+    }
+
+    /// Unmap `count` pages, splitting large pages the range only partly covers
+    fn unmap_range(&mut self, vaddr: VAddr, count: usize) -> Result {
+        for i in 0..count {
+            self.unmap(vaddr + i * PAGE_SIZE)?;
+        }
+        Ok(())
+    }
+
+    /// Update the flags of `count` pages, splitting large pages the range
+    /// only partly covers
+    fn protect_range(&mut self, vaddr: VAddr, count: usize, flags: PageTableFlags) -> Result {
+        for i in 0..count {
+            self.protect(vaddr + i * PAGE_SIZE, flags)?;
+        }
+        Ok(())
+    }
+
+    /// Read the accessed/dirty bits of the page at `vaddr`, resetting them
+    /// if `clear`
+    ///
+    /// Returns `None` if nothing is mapped there. Tables that can't inspect
+    /// the bits report every mapped page as [`PageUsage::UNKNOWN`], so
+    /// nothing ever looks idle.
+    ///
+    /// [`PageUsage::UNKNOWN`]: page_table::PageUsage::UNKNOWN
+    fn harvest(&mut self, vaddr: VAddr, _clear: bool) -> Option<(PAddr, page_table::PageUsage)> {
+        self.resolve(vaddr).map(|paddr| (paddr, page_table::PageUsage::UNKNOWN))
+    }
+
     /// Flush TLB entries for this page table
     fn flush_tlb(&self, vaddr: Option<VAddr>);
 
@@ -334,56 +391,29 @@ pub const fn pages_to_bytes(pages: usize) -> usize {
 ///
 /// This function assumes the physical mapping window is set up.
 pub unsafe fn physmap_virt_to_phys(vaddr: VAddr) -> Option<PAddr> {
+    let base = aslr::physmap_base();
+
     #[cfg(target_arch = "aarch64")]
-    {
-        let base = layout::arm64::KERNEL_PHYSMAP_BASE;
-        let size = layout::arm64::KERNEL_PHYSMAP_SIZE;
-        if vaddr >= base && vaddr - base < size {
-            Some(vaddr - base)
-        } else {
-            None
-        }
-    }
+    let size = layout::arm64::KERNEL_PHYSMAP_SIZE;
 
     #[cfg(target_arch = "x86_64")]
-    {
-        let base = layout::amd64::KERNEL_PHYSMAP_BASE;
-        let size = layout::amd64::KERNEL_PHYSMAP_SIZE;
-        if vaddr >= base && vaddr - base < size {
-            Some(vaddr - base)
-        } else {
-            None
-        }
-    }
+    let size = layout::amd64::KERNEL_PHYSMAP_SIZE;
 
     #[cfg(target_arch = "riscv64")]
-    {
-        let base = layout::riscv::KERNEL_PHYSMAP_BASE;
-        let size = layout::riscv::KERNEL_PHYSMAP_SIZE;
-        if vaddr >= base && vaddr - base < size {
-            Some(vaddr - base)
-        } else {
-            None
-        }
+    let size = layout::riscv::KERNEL_PHYSMAP_SIZE;
+
+    if vaddr >= base && vaddr - base < size {
+        Some(vaddr - base)
+    } else {
+        None
     }
 }
 
 /// Physical address to virtual address (kernel physmap only)
+///
+/// The physmap base is randomized at boot (see [`aslr`]).
 pub fn phys_to_physmap(paddr: PAddr) -> VAddr {
-    #[cfg(target_arch = "aarch64")]
-    {
-        layout::arm64::KERNEL_PHYSMAP_BASE + paddr
-    }
-
-    #[cfg(target_arch = "x86_64")]
-    {
-        layout::amd64::KERNEL_PHYSMAP_BASE + paddr
-    }
-
-    #[cfg(target_arch = "riscv64")]
-    {
-        layout::riscv::KERNEL_PHYSMAP_BASE + paddr
-    }
+    aslr::physmap_base() + paddr
 }
 
 // ============================================================================
@@ -439,4 +469,14 @@ mod tests {
         assert!(MemProt::EXEC.is_valid_wxorx());
         assert!((MemProt::WRITE | MemProt::EXEC).is_valid_wxorx()); // Invalid!
     }
+
+    #[test]
+    fn test_vm_error_status() {
+        use crate::rustux::types::{err::*, Status};
+
+        assert_eq!(Status::from(VmError::InvalidAddress), RX_ERR_INVALID_ARGS);
+        assert_eq!(Status::from(VmError::NoMemory), RX_ERR_NO_MEMORY);
+        assert_eq!(Status::from(VmError::PermissionDenied), RX_ERR_ACCESS_DENIED);
+        assert_eq!(Status::from(VmError::NotMapped), RX_ERR_NOT_FOUND);
+    }
 }
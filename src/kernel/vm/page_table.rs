@@ -22,12 +22,28 @@
 //! The page table abstraction uses a traits-based approach where each architecture
 //! implements the common `PageTableEntry` trait. The high-level `PageTable` type
 //! provides architecture-agnostic operations.
+//!
+//! # Large Pages
+//!
+//! Each architecture's walker maps a run with the largest entries that its
+//! alignment and length allow: 2MB and 1GB pages on x86-64, 2MB/1GB blocks
+//! and 64KB contiguous-hint runs on ARM64, and 2MB/1GB megapages on RISC-V.
+//! Unmapping or protecting part of a large page first splits it into
+//! next-smaller entries. [`largest_fit`](MappingType::largest_fit) picks the
+//! entry size, and every large entry created, removed or split is counted
+//! in the `vm.page_table.large_*` kcounters.
 
 
+use crate::kernel::object::vmo::CachePolicy;
 use crate::kernel::vm::layout::*;
 use crate::kernel::vm::{ArchPageTable, VmError, Result};
+use crate::KCOUNTER;
 use core::fmt;
 
+KCOUNTER!(LARGE_MAPPINGS_64K, "vm.page_table.large_64k");
+KCOUNTER!(LARGE_MAPPINGS_2M, "vm.page_table.large_2m");
+KCOUNTER!(LARGE_MAPPINGS_1G, "vm.page_table.large_1g");
+
 /// ============================================================================
 /// Page Table Flags (Cross-Architecture)
 /// ============================================================================
@@ -60,6 +76,9 @@ pub enum PageTableFlags {
     /// Dirty flag (set by hardware for writable mappings)
     Dirty = 1 << 6,
 
+    /// Write-combining (the x86 PAT bit, selecting PAT entry 4)
+    WriteCombining = 1 << 7,
+
     /// Global mapping (not flushed on TLB shootdown)
     Global = 1 << 8,
 
@@ -89,6 +108,11 @@ impl PageTableFlags {
     /// Present + user + read-only (user code)
     pub const USER_CODE: u64 = (Self::Present as u64) | (Self::User as u64);
 
+    /// Bits that select the memory type
+    pub const CACHE_MASK: u64 = (Self::WriteThrough as u64)
+        | (Self::CacheDisable as u64)
+        | (Self::WriteCombining as u64);
+
     /// Create flags from raw bits
     pub const fn from_bits(bits: u64) -> Self {
         unsafe { core::mem::transmute(bits) }
@@ -144,6 +168,19 @@ impl PageTableFlags {
         flags
     }
 
+    /// Convert a cache policy to page table flags
+    ///
+    /// The bits index the x86 PAT directly; other architectures translate
+    /// them to their own memory attributes.
+    pub const fn from_cache_policy(policy: CachePolicy) -> u64 {
+        match policy {
+            CachePolicy::Default => 0,
+            CachePolicy::WriteThrough => Self::WriteThrough as u64,
+            CachePolicy::Uncached => (Self::CacheDisable as u64) | (Self::WriteThrough as u64),
+            CachePolicy::WriteCombining => Self::WriteCombining as u64,
+        }
+    }
+
     /// Convert page table flags to memory protection
     pub const fn to_prot(self, is_user: bool) -> MemProt {
         let mut prot = MemProt::None;
@@ -230,6 +267,9 @@ pub enum MappingType {
 
     /// 1GB page (huge page)
     Page1G = 2,
+
+    /// 64KB run of 4KB pages sharing one TLB entry (ARM64 contiguous hint)
+    Page64K = 3,
 }
 
 impl MappingType {
@@ -237,19 +277,93 @@ impl MappingType {
     pub const fn size(&self) -> usize {
         match self {
             Self::Page4K => 4 * 1024,
+            Self::Page64K => 64 * 1024,
             Self::Page2M => 2 * 1024 * 1024,
             Self::Page1G => 1024 * 1024 * 1024,
         }
     }
 
+    /// Get the number of 4KB pages this mapping type covers
+    pub const fn pages(&self) -> usize {
+        self.size() / PAGE_SIZE
+    }
+
+    /// Mapping type for the large page with the given shift
+    pub const fn from_shift(shift: u32) -> Option<Self> {
+        match shift {
+            12 => Some(Self::Page4K),
+            16 => Some(Self::Page64K),
+            21 => Some(Self::Page2M),
+            30 => Some(Self::Page1G),
+            _ => None,
+        }
+    }
+
+    /// Largest entry of `supported` that can map the start of a run
+    ///
+    /// A type fits if both `vaddr` and `paddr` are aligned to its size and
+    /// at least `size` bytes remain. Falls back to `Page4K`.
+    pub fn largest_fit(vaddr: VAddr, paddr: PAddr, size: usize, supported: &[MappingType]) -> Self {
+        supported
+            .iter()
+            .copied()
+            .filter(|kind| {
+                let mask = kind.size() - 1;
+                (vaddr | paddr) & mask == 0 && size >= kind.size()
+            })
+            .max_by_key(|kind| kind.size())
+            .unwrap_or(Self::Page4K)
+    }
+
     /// Get the page shift for this mapping type
     pub const fn shift(&self) -> u8 {
         match self {
             Self::Page4K => 12,
+            Self::Page64K => 16,
             Self::Page2M => 21,
             Self::Page1G => 30,
         }
     }
+
+    /// Kcounter tracking live mappings of this type, if it is large
+    fn counter(&self) -> Option<&'static crate::kernel::lib::counters::Kcounter> {
+        match self {
+            Self::Page4K => None,
+            Self::Page64K => Some(&LARGE_MAPPINGS_64K),
+            Self::Page2M => Some(&LARGE_MAPPINGS_2M),
+            Self::Page1G => Some(&LARGE_MAPPINGS_1G),
+        }
+    }
+}
+
+/// Accessed and dirty state of one mapped page, as a harvest found it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PageUsage {
+    /// The page was read or written since the last harvest
+    pub accessed: bool,
+
+    /// The page was written since the last harvest
+    pub dirty: bool,
+}
+
+impl PageUsage {
+    /// Usage of a page whose entry can't be inspected; it is assumed in use
+    pub const UNKNOWN: Self = Self { accessed: true, dirty: true };
+}
+
+/// Record `delta` large mappings of `kind` created (positive) or removed
+/// or split (negative)
+///
+/// Called by the architecture walkers; 4KB pages aren't counted.
+pub fn count_large_mappings(kind: MappingType, delta: i64) {
+    if let Some(counter) = kind.counter() {
+        counter.add(delta);
+    }
+}
+
+/// Number of large mappings of `kind` that currently exist
+pub fn large_mappings(kind: MappingType) -> i64 {
+    kind.counter().map_or(0, |counter| counter.value())
 }
 
 /// ============================================================================
@@ -359,13 +473,7 @@ impl PageTable {
         let flags_bits = flags.enforce_wxorx();
         let flags = PageTableFlags::from_bits(flags_bits);
 
-        for i in 0..count {
-            let va = vaddr + (i * PAGE_SIZE);
-            let pa = paddr + (i * PAGE_SIZE);
-            self.inner.map(va, pa, flags)?;
-        }
-
-        Ok(())
+        self.inner.map_range(vaddr, paddr, count, flags)
     }
 
     /// Unmap a page
@@ -383,12 +491,7 @@ impl PageTable {
             return Err(VmError::AlignmentError);
         }
 
-        for i in 0..count {
-            let va = vaddr + (i * PAGE_SIZE);
-            self.inner.unmap(va)?;
-        }
-
-        Ok(())
+        self.inner.unmap_range(vaddr, count)
     }
 
     /// Resolve a virtual address to physical address
@@ -424,11 +527,29 @@ impl PageTable {
         let flags_bits = flags.enforce_wxorx();
         let flags = PageTableFlags::from_bits(flags_bits);
 
+        self.inner.protect_range(vaddr, count, flags)
+    }
+
+    /// Harvest the accessed/dirty bits of `count` pages at `vaddr`
+    ///
+    /// Calls `f` with the physical address and usage of each mapped page.
+    /// With `clear`, the bits are reset so the next harvest sees only new
+    /// accesses, and the TLB is flushed so the hardware sets them again.
+    pub fn harvest_pages(&mut self, vaddr: VAddr, count: usize, clear: bool,
+                         mut f: impl FnMut(PAddr, PageUsage)) -> Result {
+        if !is_page_aligned(vaddr) {
+            return Err(VmError::AlignmentError);
+        }
+
         for i in 0..count {
-            let va = vaddr + (i * PAGE_SIZE);
-            self.inner.protect(va, flags)?;
+            if let Some((paddr, usage)) = self.inner.harvest(vaddr + i * PAGE_SIZE, clear) {
+                f(paddr, usage);
+            }
         }
 
+        if clear {
+            self.inner.flush_tlb(None);
+        }
         Ok(())
     }
 
@@ -568,10 +689,42 @@ mod tests {
         assert!(enforced & PageTableFlags::NoExecute as u64 != 0);
     }
 
+    #[test]
+    fn test_cache_policy_flags() {
+        assert_eq!(PageTableFlags::from_cache_policy(CachePolicy::Default), 0);
+
+        for policy in [CachePolicy::Uncached, CachePolicy::WriteCombining, CachePolicy::WriteThrough] {
+            let bits = PageTableFlags::from_cache_policy(policy);
+            assert_ne!(bits, 0);
+            assert_eq!(bits & !PageTableFlags::CACHE_MASK, 0);
+        }
+    }
+
     #[test]
     fn test_mapping_type() {
         assert_eq!(MappingType::Page4K.size(), 4096);
         assert_eq!(MappingType::Page2M.size(), 2 * 1024 * 1024);
         assert_eq!(MappingType::Page1G.size(), 1024 * 1024 * 1024);
+        assert_eq!(MappingType::Page64K.pages(), 16);
+        assert_eq!(MappingType::from_shift(21), Some(MappingType::Page2M));
+        assert_eq!(MappingType::from_shift(13), None);
+    }
+
+    #[test]
+    fn test_largest_fit() {
+        let all = [MappingType::Page64K, MappingType::Page2M, MappingType::Page1G];
+        let mb2 = MappingType::Page2M.size();
+        let gb1 = MappingType::Page1G.size();
+
+        // Both addresses and the length decide
+        assert_eq!(MappingType::largest_fit(0, 0, gb1, &all), MappingType::Page1G);
+        assert_eq!(MappingType::largest_fit(gb1, 0, gb1 - 1, &all), MappingType::Page2M);
+        assert_eq!(MappingType::largest_fit(mb2, 0x1_0000, gb1, &all), MappingType::Page64K);
+        assert_eq!(MappingType::largest_fit(mb2, 0x1000, gb1, &all), MappingType::Page4K);
+        assert_eq!(MappingType::largest_fit(0, 0, 0x8000, &all), MappingType::Page4K);
+
+        // Only supported sizes are used
+        assert_eq!(MappingType::largest_fit(0, 0, gb1, &[MappingType::Page2M]), MappingType::Page2M);
+        assert_eq!(MappingType::largest_fit(0, 0, gb1, &[]), MappingType::Page4K);
     }
 }
@@ -0,0 +1,462 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Memory Pressure
+//!
+//! Free memory is sorted into four levels by three watermarks:
+//!
+//! | Level | Free memory |
+//! |---|---|
+//! | `Normal` | at or above the warning watermark |
+//! | `Warning` | below the warning watermark |
+//! | `Critical` | below the critical watermark |
+//! | `OutOfMemory` | below the out-of-memory watermark |
+//!
+//! The PMM calls [`update`] whenever it hands out or takes back pages. The
+//! level rises as soon as free memory drops below a watermark, but only
+//! falls once free memory is a debounce margin above it, so it doesn't
+//! flap around a watermark.
+//!
+//! # Events
+//!
+//! Each level has a manual-reset event; the event of the current level is
+//! signaled and the others are not. Userspace gets them from
+//! `rx_system_get_event` and waits for the level it cares about.
+//!
+//! # Reclaim and OOM
+//!
+//! At `Critical` or worse, and whenever an allocation fails, a DPC runs
+//! [`reclaim`]: first the pages of unlocked discardable VMOs are freed.
+//! If memory is still out, the OOM killer kills one job chosen by job
+//! policy (see [`job`](crate::kernel::object::job)) instead of letting the
+//! kernel fail. Allocations that fail meanwhile return `RX_ERR_NO_MEMORY`.
+//!
+//! # Command Line
+//!
+//! - `kernel.oom.warning_mb`, `kernel.oom.critical_mb`,
+//!   `kernel.oom.outofmemory_mb` - watermarks; by default 10%, 5% and 2%
+//!   of memory
+//! - `kernel.oom.debounce_mb` - margin to drop a level; 1% by default
+//! - `kernel.oom.enable` - let the OOM killer kill jobs (default true)
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::kernel::cmdline;
+use crate::kernel::dpc::Dpc;
+use crate::kernel::lib::slab;
+use crate::kernel::object::event::{Event, EventFlags};
+use crate::kernel::object::job;
+use crate::kernel::pmm;
+use crate::kernel::sync::Mutex;
+use crate::kernel::syscalls::{event as event_syscalls, object_wait, vmo as vmo_syscalls};
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+
+use crate::{log_info, log_warn};
+
+/// A memory pressure level
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PressureLevel {
+    /// Plenty of free memory
+    Normal = 0,
+
+    /// Free memory is getting low; caches should shrink
+    Warning = 1,
+
+    /// Free memory is nearly gone; discardable memory is reclaimed
+    Critical = 2,
+
+    /// Memory has run out; a job is killed
+    OutOfMemory = 3,
+}
+
+/// Number of pressure levels
+pub const LEVEL_COUNT: usize = 4;
+
+impl PressureLevel {
+    const fn from_raw(raw: u32) -> Self {
+        match raw {
+            1 => Self::Warning,
+            2 => Self::Critical,
+            3 => Self::OutOfMemory,
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// Free-page thresholds between levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watermarks {
+    /// Below this many free pages the level is at least `Warning`
+    pub warning: usize,
+
+    /// Below this many free pages the level is at least `Critical`
+    pub critical: usize,
+
+    /// Below this many free pages the level is `OutOfMemory`
+    pub out_of_memory: usize,
+
+    /// Pages above a watermark needed to drop below its level
+    pub debounce: usize,
+}
+
+impl Watermarks {
+    /// Default watermarks for `total_pages` of memory
+    pub const fn for_total(total_pages: usize) -> Self {
+        Self {
+            warning: total_pages / 10,
+            critical: total_pages / 20,
+            out_of_memory: total_pages / 50,
+            debounce: total_pages / 100,
+        }
+    }
+
+    /// Level of `free_pages` with every watermark raised by `margin`
+    const fn level(&self, free_pages: usize, margin: usize) -> PressureLevel {
+        if free_pages < self.out_of_memory + margin {
+            PressureLevel::OutOfMemory
+        } else if free_pages < self.critical + margin {
+            PressureLevel::Critical
+        } else if free_pages < self.warning + margin {
+            PressureLevel::Warning
+        } else {
+            PressureLevel::Normal
+        }
+    }
+}
+
+/// Level for `free_pages` when the level was `current`
+///
+/// Rises at once; falls only as far as the debounce margin allows.
+pub fn level_for(free_pages: usize, current: PressureLevel, watermarks: &Watermarks) -> PressureLevel {
+    let level = watermarks.level(free_pages, 0);
+    if level >= current {
+        return level;
+    }
+    watermarks.level(free_pages, watermarks.debounce).min(current)
+}
+
+/// Set once the events exist and `update` may run
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Current level, as a raw `PressureLevel`
+static LEVEL: AtomicU32 = AtomicU32::new(PressureLevel::Normal as u32);
+
+/// Watermarks, in pages
+static WARNING: AtomicUsize = AtomicUsize::new(0);
+static CRITICAL: AtomicUsize = AtomicUsize::new(0);
+static OUT_OF_MEMORY: AtomicUsize = AtomicUsize::new(0);
+static DEBOUNCE: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the OOM killer may kill jobs
+static OOM_KILL_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Level events, indexed by level
+static EVENTS: Mutex<Vec<Arc<Event>>> = Mutex::new(Vec::new());
+
+/// Handle values of the level events, indexed by level
+static EVENT_HANDLES: [AtomicU32; LEVEL_COUNT] = [const { AtomicU32::new(0) }; LEVEL_COUNT];
+
+/// Runs `reclaim` outside the allocation that noticed the pressure
+static RECLAIM_DPC: Dpc = Dpc::new();
+
+/// Jobs killed by the OOM killer
+static OOM_KILLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Read a watermark from the command line, in MiB, as pages
+fn watermark_arg(key: &str, default_pages: usize) -> usize {
+    let default_mb = (default_pages * pmm::PAGE_SIZE / (1024 * 1024)) as u64;
+    let mb = cmdline::cmdline_get_uint64(key, default_mb);
+    (mb as usize).saturating_mul(1024 * 1024 / pmm::PAGE_SIZE)
+}
+
+/// Set the watermarks and create the level events
+///
+/// Runs once the PMM knows every arena and the event registry exists.
+pub fn init() {
+    let defaults = Watermarks::for_total(pmm::pmm_count_total_pages() as usize);
+    let watermarks = Watermarks {
+        warning: watermark_arg("kernel.oom.warning_mb", defaults.warning),
+        critical: watermark_arg("kernel.oom.critical_mb", defaults.critical),
+        out_of_memory: watermark_arg("kernel.oom.outofmemory_mb", defaults.out_of_memory),
+        debounce: watermark_arg("kernel.oom.debounce_mb", defaults.debounce),
+    };
+    WARNING.store(watermarks.warning, Ordering::Relaxed);
+    CRITICAL.store(watermarks.critical, Ordering::Relaxed);
+    OUT_OF_MEMORY.store(watermarks.out_of_memory, Ordering::Relaxed);
+    DEBOUNCE.store(watermarks.debounce, Ordering::Relaxed);
+    OOM_KILL_ENABLED.store(cmdline::cmdline_get_bool("kernel.oom.enable", true), Ordering::Relaxed);
+
+    let mut events = EVENTS.lock();
+    for handle in EVENT_HANDLES.iter() {
+        let event = Arc::new(Event::new(false, EventFlags::MANUAL_RESET));
+        match event_syscalls::register_kernel_event(event.clone()) {
+            Ok(value) => handle.store(value, Ordering::Relaxed),
+            Err(err) => {
+                log_warn!("memory pressure: no event: {}", err);
+            }
+        }
+        events.push(event);
+    }
+
+    let level = watermarks.level(pmm::pmm_count_free_pages() as usize, 0);
+    LEVEL.store(level as u32, Ordering::Release);
+    events[level as usize].signal();
+    drop(events);
+
+    RECLAIM_DPC.set_callback(reclaim_dpc);
+    INITIALIZED.store(true, Ordering::Release);
+
+    log_info!(
+        "memory pressure: watermarks {}/{}/{} pages, now {:?}",
+        watermarks.warning,
+        watermarks.critical,
+        watermarks.out_of_memory,
+        level
+    );
+}
+
+/// Current watermarks
+pub fn watermarks() -> Watermarks {
+    Watermarks {
+        warning: WARNING.load(Ordering::Relaxed),
+        critical: CRITICAL.load(Ordering::Relaxed),
+        out_of_memory: OUT_OF_MEMORY.load(Ordering::Relaxed),
+        debounce: DEBOUNCE.load(Ordering::Relaxed),
+    }
+}
+
+/// Current pressure level
+pub fn level() -> PressureLevel {
+    PressureLevel::from_raw(LEVEL.load(Ordering::Acquire))
+}
+
+/// Handle value of the event signaled while at `level`
+pub fn event_handle(level: PressureLevel) -> Option<u32> {
+    match EVENT_HANDLES[level as usize].load(Ordering::Relaxed) {
+        0 => None,
+        handle => Some(handle),
+    }
+}
+
+/// Jobs killed by the OOM killer since boot
+pub fn oom_kills() -> usize {
+    OOM_KILLS.load(Ordering::Relaxed)
+}
+
+/// Recompute the level after free memory changed
+///
+/// Called by the PMM; cheap unless the level changes.
+pub fn update() {
+    if !INITIALIZED.load(Ordering::Acquire) {
+        return;
+    }
+
+    let free = pmm::pmm_count_free_pages() as usize;
+    let current = level();
+    let level = level_for(free, current, &watermarks());
+    if level == current {
+        return;
+    }
+
+    // Another CPU may have moved it first; it publishes its own change
+    if LEVEL
+        .compare_exchange(current as u32, level as u32, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return;
+    }
+
+    {
+        let events = EVENTS.lock();
+        events[current as usize].unsignal();
+        events[level as usize].signal();
+    }
+    if let Some(handle) = event_handle(level) {
+        // Waiters see the same signal rx_object_signal raises on an event
+        object_wait::wake_waiters(handle, object_wait::signal::USER_0);
+    }
+
+    if level > current {
+        log_warn!("memory pressure: {:?} ({} pages free)", level, free);
+    } else {
+        log_info!("memory pressure: {:?} ({} pages free)", level, free);
+    }
+
+    if level >= PressureLevel::Critical {
+        let _ = RECLAIM_DPC.queue(false);
+    }
+}
+
+/// Note that an allocation found no free page
+///
+/// Called by the PMM; reclaim runs soon after, so a retry may succeed.
+pub fn allocation_failed() {
+    if INITIALIZED.load(Ordering::Acquire) {
+        update();
+        let _ = RECLAIM_DPC.queue(false);
+    }
+}
+
+unsafe fn reclaim_dpc(_dpc: &Dpc) {
+    reclaim();
+}
+
+/// Free memory until the level is below `Critical`
+///
+/// Discards unlocked discardable VMOs, then, if memory is still out, kills
+/// one job. Returns the number of pages discarded.
+pub fn reclaim() -> usize {
+    // Pre-zeroed pages are the cheapest to give back
+    let drained = pmm::pmm_drain_zero_pages();
+    if drained > 0 {
+        log_info!("memory pressure: released {} pre-zeroed pages", drained);
+    }
+    let reaped = slab::reap_all();
+    if reaped > 0 {
+        log_info!("memory pressure: released {} empty slabs", reaped);
+    }
+
+    let watermarks = watermarks();
+    let free = pmm::pmm_count_free_pages() as usize;
+    let target = (watermarks.critical + watermarks.debounce).saturating_sub(free);
+
+    let discarded = if target > 0 {
+        vmo_syscalls::reclaim_discardable(target)
+    } else {
+        0
+    };
+    if discarded > 0 {
+        log_info!("memory pressure: discarded {} pages", discarded);
+    }
+    update();
+
+    if level() == PressureLevel::OutOfMemory || pmm::pmm_count_free_pages() == 0 {
+        let _ = oom_kill();
+    }
+    discarded
+}
+
+/// Kill the job chosen by job policy
+///
+/// # Errors
+///
+/// - `RX_ERR_NOT_SUPPORTED` - the OOM killer is disabled
+/// - `RX_ERR_NOT_FOUND` - no job has kill-on-OOM set
+pub fn oom_kill() -> Result<job::JobId> {
+    if !OOM_KILL_ENABLED.load(Ordering::Relaxed) {
+        log_warn!("memory pressure: out of memory, OOM killer disabled");
+        return Err(RX_ERR_NOT_SUPPORTED);
+    }
+
+    let victim = job::select_oom_victim(&job::oom_candidates())
+        .and_then(job::lookup)
+        .ok_or_else(|| {
+            log_warn!("memory pressure: out of memory, no job to kill");
+            RX_ERR_NOT_FOUND
+        })?;
+
+    log_warn!(
+        "memory pressure: out of memory, killing job {} (importance {})",
+        victim.id,
+        victim.importance()
+    );
+    victim.kill_with_code(job::TASK_RETCODE_OOM_KILL);
+    OOM_KILLS.fetch_add(1, Ordering::Relaxed);
+    Ok(victim.id)
+}
+
+/// ============================================================================
+/// Shell Command
+/// ============================================================================
+
+fn cmd_pressure(argc: i32, argv: &[&str], _flags: u32) -> i32 {
+    if argc >= 2 {
+        match argv[1] {
+            "reclaim" => {
+                crate::println!("discarded {} pages", reclaim());
+                return 0;
+            }
+            "oom" => {
+                return match oom_kill() {
+                    Ok(id) => {
+                        crate::println!("killed job {}", id);
+                        0
+                    }
+                    Err(err) => {
+                        crate::println!("oom: error {}", err);
+                        err
+                    }
+                };
+            }
+            _ => {
+                crate::println!("usage: pressure [reclaim|oom]");
+                return -1;
+            }
+        }
+    }
+
+    let watermarks = watermarks();
+    crate::println!(
+        "level {:?}, {} pages free of {}",
+        level(),
+        pmm::pmm_count_free_pages(),
+        pmm::pmm_count_total_pages()
+    );
+    crate::println!(
+        "watermarks: warning {} critical {} oom {} debounce {} pages",
+        watermarks.warning,
+        watermarks.critical,
+        watermarks.out_of_memory,
+        watermarks.debounce
+    );
+    crate::println!("oom kills: {}", oom_kills());
+    0
+}
+
+crate::static_command!("pressure", "memory pressure, or pressure reclaim|oom", cmd_pressure);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATERMARKS: Watermarks = Watermarks {
+        warning: 1000,
+        critical: 500,
+        out_of_memory: 200,
+        debounce: 50,
+    };
+
+    #[test]
+    fn test_level_rises() {
+        assert_eq!(level_for(2000, PressureLevel::Normal, &WATERMARKS), PressureLevel::Normal);
+        assert_eq!(level_for(999, PressureLevel::Normal, &WATERMARKS), PressureLevel::Warning);
+        assert_eq!(level_for(499, PressureLevel::Normal, &WATERMARKS), PressureLevel::Critical);
+        assert_eq!(level_for(0, PressureLevel::Warning, &WATERMARKS), PressureLevel::OutOfMemory);
+    }
+
+    #[test]
+    fn test_level_debounce() {
+        // Just above the watermark isn't enough to drop a level
+        assert_eq!(level_for(520, PressureLevel::Critical, &WATERMARKS), PressureLevel::Critical);
+        assert_eq!(level_for(550, PressureLevel::Critical, &WATERMARKS), PressureLevel::Warning);
+
+        // A big jump drops several levels, but never raises one
+        assert_eq!(level_for(5000, PressureLevel::OutOfMemory, &WATERMARKS), PressureLevel::Normal);
+        assert_eq!(level_for(1020, PressureLevel::Warning, &WATERMARKS), PressureLevel::Warning);
+    }
+
+    #[test]
+    fn test_default_watermarks() {
+        let watermarks = Watermarks::for_total(100_000);
+        assert!(watermarks.warning > watermarks.critical);
+        assert!(watermarks.critical > watermarks.out_of_memory);
+        assert!(watermarks.debounce > 0);
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Shared Zero Page
+//!
+//! One physical page of zeros, allocated at boot and never freed. A read
+//! fault on untouched anonymous memory maps it read-only instead of
+//! allocating, so memory that is only ever read costs no pages. The first
+//! write or instruction fetch to such a page faults again, and the fault
+//! handler commits the VMO's page and replaces the zero page with it,
+//! mapped with the mapping's protection (see [`fault`](super::fault)).
+//!
+//! VMO pages come from the PMM's zeroed page allocator, which keeps the
+//! counters for pre-zeroed versus zero-on-demand allocations.
+
+use crate::kernel::pmm;
+use crate::kernel::vm::aspace::AddressSpace;
+use crate::kernel::vm::layout::MemProt;
+use crate::rustux::types::*;
+use crate::rustux::types::err::*;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// Import logging macros
+use crate::{log_error, log_info};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(ZERO_PAGE_MAPS, "vm.zero_page.shared.maps");
+KCOUNTER!(ZERO_PAGE_BREAKS, "vm.zero_page.shared.breaks");
+
+/// Physical address of the shared zero page, 0 until [`init`]
+static ZERO_PAGE: AtomicU64 = AtomicU64::new(0);
+
+/// Allocate the shared zero page
+///
+/// Called once the PMM is up. Without it, read faults fall back to
+/// allocating private pages.
+pub fn init() {
+    match pmm::pmm_alloc_zeroed_page(pmm::PMM_ALLOC_FLAG_ANY) {
+        Ok(paddr) => {
+            ZERO_PAGE.store(paddr, Ordering::Release);
+            log_info!("Shared zero page at {:#x}", paddr);
+        }
+        Err(err) => {
+            log_error!("zero page: allocation failed: {:?}", err);
+        }
+    }
+}
+
+/// Physical address of the shared zero page, if allocated
+pub fn paddr() -> Option<PAddr> {
+    match ZERO_PAGE.load(Ordering::Acquire) {
+        0 => None,
+        paddr => Some(paddr),
+    }
+}
+
+/// Whether `paddr` is the shared zero page
+pub fn is_zero_page(paddr: PAddr) -> bool {
+    paddr != 0 && paddr == ZERO_PAGE.load(Ordering::Acquire)
+}
+
+/// Map the shared zero page read-only at `vaddr`
+///
+/// # Errors
+///
+/// - `RX_ERR_BAD_STATE` - the zero page was never allocated
+/// - errors from mapping into `aspace`
+pub fn map(aspace: &AddressSpace, vaddr: VAddr) -> Result {
+    let paddr = paddr().ok_or(RX_ERR_BAD_STATE)?;
+    aspace.map(vaddr, paddr as usize, 1, MemProt::Read)?;
+    ZERO_PAGE_MAPS.add(1);
+    Ok(())
+}
+
+/// Replace the shared zero page at `vaddr` with `paddr` mapped `prot`
+///
+/// `paddr` is the committed page of the VMO mapped at `vaddr`, and `prot`
+/// the protection of that mapping.
+pub fn replace(aspace: &AddressSpace, vaddr: VAddr, paddr: PAddr, prot: MemProt) -> Result {
+    aspace
+        .unmap(vaddr, 1)
+        .and_then(|()| aspace.map(vaddr, paddr as usize, 1, prot))?;
+    aspace.flush_tlb_va(vaddr);
+
+    ZERO_PAGE_BREAKS.add(1);
+    Ok(())
+}
+
+/// Number of read faults satisfied with the shared zero page
+pub fn map_count() -> i64 {
+    ZERO_PAGE_MAPS.value()
+}
+
+/// Number of writes that replaced the shared zero page
+pub fn break_count() -> i64 {
+    ZERO_PAGE_BREAKS.value()
+}
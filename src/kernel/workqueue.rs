@@ -0,0 +1,486 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Workqueues
+//!
+//! A workqueue is a named pool of kernel worker threads that run [`Work`]
+//! items off a shared FIFO. It is the place for background kernel work
+//! that may block or take a while, such as zeroing pages or reclaiming
+//! VMOs. Unlike DPCs, work items never run in interrupt context.
+//!
+//! # Design
+//!
+//! - **Static work items**: Work items are `'static`, usually statics,
+//!   like DPCs. Queueing an item that is already pending is a no-op, so a
+//!   burst of requests costs one run.
+//! - **Delayed work**: [`Workqueue::queue_delayed`] parks the item on a
+//!   deadline-sorted list; a worker moves it to the FIFO once the
+//!   deadline passes.
+//! - **Cancellation**: [`Workqueue::cancel`] takes a pending item off the
+//!   queue and reports whether it was pending. A run already in progress
+//!   finishes; [`Workqueue::cancel_sync`] also waits for it.
+//! - **Requeueing**: An item queued while it runs is run again afterwards,
+//!   which lets a work function reschedule itself.
+//! - **System workqueue**: [`system`] is shared by subsystems that don't
+//!   need threads of their own.
+//!
+//! All state transitions of an item happen under its queue's lock, so
+//! [`Workqueue::cancel`] never misses an item that is being queued.
+//!
+//! # Usage
+//!
+//! ```rust
+//! static REFILL: Work = Work::new(refill);
+//!
+//! fn refill(_work: &'static Work) {
+//!     // May allocate and block
+//! }
+//!
+//! workqueue::system().queue(&REFILL);
+//! workqueue::system().queue_delayed(&REFILL, 10_000_000); // in 10ms
+//! ```
+
+use crate::kernel::percpu;
+use crate::kernel::sync::{Event, EventFlags, Mutex};
+use crate::kernel::thread::{self, ThreadId, ThreadPriority};
+use crate::kernel::timer;
+use crate::rustux::types::*;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// Import logging macros
+use crate::{log_debug, log_error, log_info};
+
+// Import KCOUNTER macro at crate level
+use crate::KCOUNTER;
+
+KCOUNTER!(WORK_QUEUED, "kernel.workqueue.queued");
+KCOUNTER!(WORK_COALESCED, "kernel.workqueue.coalesced");
+KCOUNTER!(WORK_RUN, "kernel.workqueue.run");
+KCOUNTER!(WORK_CANCELED, "kernel.workqueue.canceled");
+
+/// ============================================================================
+/// Work Items
+/// ============================================================================
+
+/// Work function type
+pub type WorkFn = fn(work: &'static Work);
+
+/// Work item is on a queue's FIFO or delayed list
+const WORK_PENDING: u32 = 1 << 0;
+
+/// Work item's function is running
+const WORK_RUNNING: u32 = 1 << 1;
+
+/// A unit of background work
+pub struct Work {
+    /// Function to run
+    func: WorkFn,
+
+    /// WORK_* state bits
+    state: AtomicU32,
+
+    /// When a delayed item becomes ready, in nanoseconds
+    deadline: AtomicU64,
+}
+
+impl Work {
+    /// Create a work item that runs `func`
+    pub const fn new(func: WorkFn) -> Self {
+        Self {
+            func,
+            state: AtomicU32::new(0),
+            deadline: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the item is waiting to run
+    pub fn is_pending(&self) -> bool {
+        self.state.load(Ordering::Acquire) & WORK_PENDING != 0
+    }
+
+    /// Whether the item's function is running
+    pub fn is_running(&self) -> bool {
+        self.state.load(Ordering::Acquire) & WORK_RUNNING != 0
+    }
+}
+
+/// ============================================================================
+/// Workqueue
+/// ============================================================================
+
+/// Default priority of workqueue threads
+pub const WORKQUEUE_PRIORITY: ThreadPriority = thread::PRIORITY_DEFAULT;
+
+/// Items waiting on a workqueue
+struct Pending {
+    /// Items ready to run, in queue order
+    ready: VecDeque<&'static Work>,
+
+    /// Delayed items, earliest deadline first
+    delayed: Vec<&'static Work>,
+}
+
+impl Pending {
+    /// Move delayed items whose deadline is at or before `now` to `ready`
+    fn promote(&mut self, now: u64) {
+        let expired = self
+            .delayed
+            .iter()
+            .take_while(|work| work.deadline.load(Ordering::Relaxed) <= now)
+            .count();
+        self.ready.extend(self.delayed.drain(..expired));
+    }
+
+    /// Take `work` off whichever list holds it
+    fn remove(&mut self, work: &'static Work) -> bool {
+        if let Some(pos) = self.ready.iter().position(|w| core::ptr::eq(*w, work)) {
+            self.ready.remove(pos);
+            return true;
+        }
+        if let Some(pos) = self.delayed.iter().position(|w| core::ptr::eq(*w, work)) {
+            self.delayed.remove(pos);
+            return true;
+        }
+        false
+    }
+
+    /// Earliest delayed deadline, or `u64::MAX` with none
+    fn next_deadline(&self) -> u64 {
+        self.delayed
+            .first()
+            .map_or(u64::MAX, |work| work.deadline.load(Ordering::Relaxed))
+    }
+}
+
+/// A named pool of kernel worker threads
+pub struct Workqueue {
+    /// Name, also given to the worker threads
+    name: &'static str,
+
+    /// Waiting items
+    pending: Mutex<Pending>,
+
+    /// Wakes a worker when work arrives or a delay changes
+    event: Event,
+
+    /// Worker threads
+    workers: Mutex<Vec<ThreadId>>,
+}
+
+impl Workqueue {
+    /// Create a workqueue with no workers
+    ///
+    /// Items can be queued at once; they run after [`Workqueue::start`].
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            pending: Mutex::new(Pending {
+                ready: VecDeque::new(),
+                delayed: Vec::new(),
+            }),
+            event: Event::new(false, EventFlags::auto_unsignal()),
+            workers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Name of the workqueue
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Start `threads` worker threads at `priority`
+    pub fn start(&'static self, threads: usize, priority: ThreadPriority) -> Result {
+        for _ in 0..threads {
+            let worker = thread::Thread::new_kernel(
+                workqueue_worker,
+                self as *const Workqueue as usize,
+                priority,
+            )?;
+            worker.set_name(self.name);
+            let tid = worker.tid();
+            worker.start()?;
+            thread::register_thread(worker.into_ref());
+            self.workers.lock().push(tid);
+        }
+
+        let mut registry = WORKQUEUES.lock();
+        if !registry.iter().any(|wq| core::ptr::eq(*wq, self)) {
+            registry.push(self);
+        }
+
+        log_debug!("workqueue {}: started {} workers", self.name, threads);
+        Ok(())
+    }
+
+    /// Queue `work` to run as soon as a worker is free
+    ///
+    /// Returns false if it was already pending.
+    pub fn queue(&self, work: &'static Work) -> bool {
+        {
+            let mut pending = self.pending.lock();
+            if !Self::mark_pending(work) {
+                return false;
+            }
+            pending.ready.push_back(work);
+        }
+        self.event.signal();
+        true
+    }
+
+    /// Queue `work` to run `delay` nanoseconds from now
+    ///
+    /// Returns false if it was already pending; its deadline is then left
+    /// alone.
+    pub fn queue_delayed(&self, work: &'static Work, delay: u64) -> bool {
+        if delay == 0 {
+            return self.queue(work);
+        }
+
+        let deadline = timer::current_time().saturating_add(delay);
+        {
+            let mut pending = self.pending.lock();
+            if !Self::mark_pending(work) {
+                return false;
+            }
+            work.deadline.store(deadline, Ordering::Relaxed);
+            let pos = pending
+                .delayed
+                .iter()
+                .position(|w| w.deadline.load(Ordering::Relaxed) > deadline)
+                .unwrap_or(pending.delayed.len());
+            pending.delayed.insert(pos, work);
+        }
+
+        // A worker may be sleeping until a later deadline
+        self.event.signal();
+        true
+    }
+
+    /// Take `work` off the queue if it is pending
+    ///
+    /// Returns whether it was pending. A run already in progress is not
+    /// affected.
+    pub fn cancel(&self, work: &'static Work) -> bool {
+        let mut pending = self.pending.lock();
+        if !pending.remove(work) {
+            return false;
+        }
+        work.state.fetch_and(!WORK_PENDING, Ordering::AcqRel);
+        WORK_CANCELED.add(1);
+        true
+    }
+
+    /// Cancel `work` and wait for a run in progress to finish
+    ///
+    /// Must not be called from `work`'s own function.
+    pub fn cancel_sync(&self, work: &'static Work) -> bool {
+        let canceled = self.cancel(work);
+        while work.is_running() {
+            thread::yield_current();
+        }
+        canceled
+    }
+
+    /// Number of items waiting, ready and delayed
+    pub fn pending_count(&self) -> usize {
+        let pending = self.pending.lock();
+        pending.ready.len() + pending.delayed.len()
+    }
+
+    /// Run every item that is ready at `now`
+    ///
+    /// Returns the number of items run. Workers call this; it is public
+    /// so early boot code can drain a queue before threads exist.
+    pub fn process(&self, now: u64) -> usize {
+        let mut runs = 0;
+        while let Ok(work) = self.next(now) {
+            Self::run(work);
+            runs += 1;
+        }
+        runs
+    }
+
+    /// Set `work` pending, under the queue lock
+    ///
+    /// Returns false if it already was.
+    fn mark_pending(work: &'static Work) -> bool {
+        let prev = work.state.fetch_or(WORK_PENDING, Ordering::AcqRel);
+        if prev & WORK_PENDING != 0 {
+            WORK_COALESCED.add(1);
+            return false;
+        }
+        WORK_QUEUED.add(1);
+        true
+    }
+
+    /// Take the next ready item and mark it running
+    ///
+    /// With none ready, returns the deadline to sleep until.
+    fn next(&self, now: u64) -> core::result::Result<&'static Work, u64> {
+        let mut pending = self.pending.lock();
+        pending.promote(now);
+        match pending.ready.pop_front() {
+            Some(work) => {
+                work.state.store(WORK_RUNNING, Ordering::Release);
+                Ok(work)
+            }
+            None => Err(pending.next_deadline()),
+        }
+    }
+
+    /// Run `work`, which [`Workqueue::next`] marked running
+    fn run(work: &'static Work) {
+        (work.func)(work);
+        work.state.fetch_and(!WORK_RUNNING, Ordering::AcqRel);
+        WORK_RUN.add(1);
+    }
+}
+
+/// Workqueue worker thread entry; `arg` is the `&'static Workqueue`
+extern "C" fn workqueue_worker(arg: usize) -> ! {
+    let wq = unsafe { &*(arg as *const Workqueue) };
+
+    loop {
+        match wq.next(timer::current_time()) {
+            Ok(work) => Workqueue::run(work),
+            Err(deadline) => {
+                let _ = wq.event.wait_deadline(deadline);
+            }
+        }
+    }
+}
+
+/// ============================================================================
+/// System Workqueue
+/// ============================================================================
+
+/// Workqueues with started workers, for the console command
+static WORKQUEUES: Mutex<Vec<&'static Workqueue>> = Mutex::new(Vec::new());
+
+/// Shared workqueue for general background work
+static SYSTEM_WORKQUEUE: Workqueue = Workqueue::new("system");
+
+/// The shared system workqueue
+pub fn system() -> &'static Workqueue {
+    &SYSTEM_WORKQUEUE
+}
+
+/// Start the system workqueue, one worker per CPU
+pub fn init() {
+    let threads = (percpu::num_cpus() as usize).max(1);
+    match SYSTEM_WORKQUEUE.start(threads, WORKQUEUE_PRIORITY) {
+        Ok(()) => {
+            log_info!("System workqueue started with {} workers", threads);
+        }
+        Err(err) => {
+            log_error!("workqueue: system workers failed to start: {:?}", err);
+        }
+    }
+}
+
+/// `workqueue` console command
+fn cmd_workqueue(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    crate::println!("{:<16} {:>8} {:>8} {:>8}", "name", "workers", "ready", "delayed");
+    for wq in WORKQUEUES.lock().iter() {
+        let pending = wq.pending.lock();
+        crate::println!(
+            "{:<16} {:>8} {:>8} {:>8}",
+            wq.name,
+            wq.workers.lock().len(),
+            pending.ready.len(),
+            pending.delayed.len()
+        );
+    }
+    0
+}
+
+crate::static_command!("workqueue", "list workqueues and pending work", cmd_workqueue);
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_coalesces() {
+        static RUNS: AtomicU32 = AtomicU32::new(0);
+        fn count(_work: &'static Work) {
+            RUNS.fetch_add(1, Ordering::Relaxed);
+        }
+        static WORK: Work = Work::new(count);
+        let wq = Workqueue::new("test");
+
+        assert!(wq.queue(&WORK));
+        assert!(!wq.queue(&WORK));
+        assert!(WORK.is_pending());
+        assert_eq!(wq.pending_count(), 1);
+
+        assert_eq!(wq.process(0), 1);
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+        assert!(!WORK.is_pending());
+        assert!(!WORK.is_running());
+    }
+
+    #[test]
+    fn test_requeue_from_work_runs_again() {
+        static RUNS: AtomicU32 = AtomicU32::new(0);
+        static WQ: Workqueue = Workqueue::new("test");
+        fn again(work: &'static Work) {
+            if RUNS.fetch_add(1, Ordering::Relaxed) == 0 {
+                assert!(work.is_running());
+                assert!(WQ.queue(work));
+            }
+        }
+        static WORK: Work = Work::new(again);
+
+        WQ.queue(&WORK);
+        assert_eq!(WQ.process(0), 2);
+        assert_eq!(RUNS.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_cancel() {
+        fn nop(_work: &'static Work) {}
+        static WORK: Work = Work::new(nop);
+        let wq = Workqueue::new("test");
+
+        assert!(!wq.cancel(&WORK));
+        wq.queue(&WORK);
+        assert!(wq.cancel(&WORK));
+        assert!(!WORK.is_pending());
+        assert_eq!(wq.process(0), 0);
+
+        // Canceled items can be queued again
+        assert!(wq.queue(&WORK));
+    }
+
+    #[test]
+    fn test_delayed_order() {
+        fn nop(_work: &'static Work) {}
+        static A: Work = Work::new(nop);
+        static B: Work = Work::new(nop);
+        let mut pending = Pending { ready: VecDeque::new(), delayed: Vec::new() };
+
+        A.deadline.store(200, Ordering::Relaxed);
+        B.deadline.store(100, Ordering::Relaxed);
+        pending.delayed.push(&B);
+        pending.delayed.push(&A);
+        assert_eq!(pending.next_deadline(), 100);
+
+        pending.promote(150);
+        assert_eq!(pending.ready.len(), 1);
+        assert!(core::ptr::eq(pending.ready[0], &B));
+        assert_eq!(pending.next_deadline(), 200);
+
+        assert!(pending.remove(&A));
+        assert_eq!(pending.next_deadline(), u64::MAX);
+    }
+}
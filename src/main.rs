@@ -15,6 +15,7 @@
 #![feature(asm)]
 #![feature(asm_experimental_arch)]
 #![feature(register_tool)]
+#![feature(allocator_api)]
 #![register_tool(no_sanitize)]
 #![register_tool(no_return)]
 #![allow(dead_code)]
@@ -249,16 +250,42 @@ macro_rules! goto_err {
     };
 }
 
+/// Register a kernel shell command
+///
+/// `static_command!("name", "help text", handler)` makes `handler` (a
+/// `console::CmdFunc`) available from the shell; an optional fourth
+/// argument gives the `CMD_AVAIL_*` flags. The command lands in the
+/// `kcmd.<name>` linker section, so registration costs nothing at boot.
 #[macro_export]
 macro_rules! static_command {
-    ($name:expr, $help:expr, $cmd:expr) => {
-        const _STATIC_CMD_NAME: &'static str = $name;
-        const _STATIC_CMD_HELP: &'static str = $help;
-        const _STATIC_CMD_CMD: &'static str = $cmd;
+    ($name:expr, $help:expr, $func:path) => {
+        $crate::static_command!($name, $help, $func, $crate::kernel::lib::console::CMD_AVAIL_NORMAL);
     };
-    ($name:expr, $help:expr) => {
-        const _STATIC_CMD_NAME: &'static str = $name;
-        const _STATIC_CMD_HELP: &'static str = $help;
+    ($name:expr, $help:expr, $func:path, $flags:expr) => {
+        const _: () = {
+            #[used]
+            #[link_section = concat!("kcmd.", $name)]
+            static CMD: $crate::kernel::lib::console::Cmd =
+                $crate::kernel::lib::console::Cmd::new($name, $help, $func, $flags);
+        };
+    };
+}
+
+/// Register a driver's suspend and resume callbacks
+///
+/// `static_pm_ops!("name", suspend, resume)` has `suspend` (a
+/// `fn() -> Result`) called when the system suspends and `resume` (a
+/// `fn()`) when it resumes. The callbacks land in the `kpm.<name>` linker
+/// section; see `kernel::power::suspend`.
+#[macro_export]
+macro_rules! static_pm_ops {
+    ($name:expr, $suspend:path, $resume:path) => {
+        const _: () = {
+            #[used]
+            #[link_section = concat!("kpm.", $name)]
+            static OPS: $crate::kernel::power::suspend::PmOps =
+                $crate::kernel::power::suspend::PmOps::new($name, $suspend, $resume);
+        };
     };
 }
 
@@ -321,9 +348,23 @@ pub use kernel::mmu;
 /// Kernel entry point
 ///
 /// This function is called by the bootloader after setting up
-/// a basic execution environment.
+/// a basic execution environment. The UEFI loader passes the physical
+/// address of its `KernelHandoff` block in the first argument register;
+/// Multiboot2 and PVH boots arrive here via `kernel::boot::boot_entry64`
+/// with a normalized handoff, and Limine enters with null.
+///
+/// # Safety
+///
+/// Called once, on the boot CPU. `handoff` must be null or point to a
+/// `KernelHandoff` that stays mapped for the life of the kernel.
 #[no_mangle]
-pub extern "C" fn kmain() -> ! {
+pub unsafe extern "C" fn kmain(handoff: *const kernel::handoff::KernelHandoff) -> ! {
+    // Record the boot handoff before anything consumes it
+    unsafe {
+        let handoff = kernel::boot::resolve_handoff(handoff);
+        kernel::handoff::set(handoff);
+    }
+
     // Initialize the kernel
     kernel::init();
 
@@ -338,10 +379,8 @@ pub extern "C" fn kmain() -> ! {
 /// This function is called when the kernel encounters a panic.
 #[cfg(not(test))]
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {
-        core::hint::spin_loop();
-    }
+fn panic(info: &PanicInfo) -> ! {
+    kernel::panic::kernel_panic(info)
 }
 
 /// Exception handling personality function
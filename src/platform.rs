@@ -20,6 +20,9 @@ pub const HALT_REASON_SW_PANIC: u32 = 0x1;
 /// Halt reason: user request
 pub const HALT_REASON_USER_REQUEST: u32 = 0x2;
 
+/// Halt reason: the kernel watchdog expired
+pub const HALT_REASON_SW_WATCHDOG: u32 = 0x3;
+
 /// Halt action: halt the system
 pub const HALT_ACTION_HALT: u32 = 0x1;
 
@@ -29,6 +32,12 @@ pub const HALT_ACTION_REBOOT: u32 = 0x2;
 /// Halt action: shutdown the system
 pub const HALT_ACTION_SHUTDOWN: u32 = 0x3;
 
+/// Halt action: reboot into the bootloader
+pub const HALT_ACTION_REBOOT_BOOTLOADER: u32 = 0x4;
+
+/// Halt action: reboot into recovery
+pub const HALT_ACTION_REBOOT_RECOVERY: u32 = 0x5;
+
 // ============================================================================
 // Platform Functions
 // ============================================================================
@@ -39,19 +48,251 @@ pub fn platform_init_mmu_mappings() {
 }
 
 /// Platform halt
-pub fn platform_halt(_reason: u32, _action: u32) -> ! {
+///
+/// Stops the machine for `reason`. `action` selects between halting,
+/// rebooting and powering off; if the platform can't reboot or power off,
+/// the machine halts instead. Only PSCI can reboot into the bootloader or
+/// recovery; elsewhere those reboot normally.
+pub fn platform_halt(reason: u32, action: u32) -> ! {
+    if reason == HALT_REASON_SW_PANIC || reason == HALT_REASON_SW_WATCHDOG {
+        crate::kernel::debug::print_internal("Halting after panic\n");
+    }
+
+    match action {
+        HALT_ACTION_REBOOT => reboot(RebootMode::Normal),
+        HALT_ACTION_REBOOT_BOOTLOADER => reboot(RebootMode::Bootloader),
+        HALT_ACTION_REBOOT_RECOVERY => reboot(RebootMode::Recovery),
+        HALT_ACTION_SHUTDOWN => power_off(),
+        _ => {}
+    }
+
+    platform_halt_cpu();
+}
+
+/// Stop the current CPU for good
+#[no_mangle]
+pub extern "C" fn platform_halt_cpu() -> ! {
     loop {
-        core::hint::spin_loop();
+        unsafe {
+            #[cfg(target_arch = "x86_64")]
+            core::arch::asm!("cli; hlt", options(nomem, nostack));
+
+            #[cfg(target_arch = "aarch64")]
+            core::arch::asm!("msr daifset, #0xf; wfi", options(nomem, nostack));
+
+            #[cfg(target_arch = "riscv64")]
+            core::arch::asm!("csrci sstatus, 0x2; wfi", options(nomem, nostack));
+        }
     }
 }
 
 /// Platform panic start
-pub fn platform_panic_start() -> ! {
-    loop {
-        core::hint::spin_loop();
+///
+/// Masks interrupts on this CPU and asks every other CPU to halt, so the
+/// panic output isn't interleaved with anything else.
+pub fn platform_panic_start() {
+    unsafe {
+        #[cfg(target_arch = "x86_64")]
+        core::arch::asm!("cli", options(nomem, nostack));
+
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("msr daifset, #0x3", options(nomem, nostack));
+
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("csrci sstatus, 0x2", options(nomem, nostack));
+    }
+
+    use crate::kernel::mp::{mp_send_ipi, MpIpiTarget, MpIpiType};
+    mp_send_ipi(MpIpiTarget::AllButLocal, 0, MpIpiType::Halt);
+}
+
+/// Read a character from the debug console without blocking
+pub fn platform_dgetc() -> Option<u8> {
+    #[cfg(target_arch = "aarch64")]
+    return crate::kernel::dev::uart::pl011::pl011_getc(false);
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use crate::kernel::arch::amd64::include::arch::amd64::inp;
+
+        // 16550 on COM1: data ready in LSR bit 0
+        const COM1: u16 = 0x3f8;
+        return if inp(COM1 + 5) & 0x01 != 0 { Some(inp(COM1)) } else { None };
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        // Legacy SBI console_getchar, -1 when nothing is pending
+        let c: i64;
+        core::arch::asm!("ecall", inlateout("a0") 0i64 => c, in("a7") 2u64, options(nostack));
+        return if c >= 0 { Some(c as u8) } else { None };
+    }
+}
+
+/// Read a character from the debug console by polling the UART
+///
+/// Unlike [`platform_dgetc`] this never depends on the receive interrupt,
+/// so it works with interrupts disabled (the panic path, the GDB stub).
+pub fn platform_pgetc() -> Option<u8> {
+    #[cfg(target_arch = "aarch64")]
+    return crate::kernel::dev::uart::pl011::pl011_pgetc();
+
+    #[cfg(not(target_arch = "aarch64"))]
+    return platform_dgetc();
+}
+
+/// Write a character to the debug console by polling the UART
+pub fn platform_pputc(c: u8) {
+    #[cfg(target_arch = "aarch64")]
+    crate::kernel::dev::uart::pl011::pl011_pputc(c);
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use crate::kernel::arch::amd64::include::arch::amd64::{inp, outp};
+
+        // Wait for the transmit holding register to empty (LSR bit 5)
+        const COM1: u16 = 0x3f8;
+        while inp(COM1 + 5) & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+        outp(COM1, c);
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        // Legacy SBI console_putchar
+        core::arch::asm!("ecall", inlateout("a0") c as u64 => _, in("a7") 1u64, options(nostack));
+    }
+}
+
+/// What to reboot into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RebootMode {
+    Normal,
+    Bootloader,
+    Recovery,
+}
+
+/// Reboot the machine, returning only if no method worked
+fn reboot(mode: RebootMode) {
+    #[cfg(target_arch = "aarch64")]
+    {
+        use crate::kernel::dev::psci::{self, RebootFlags};
+
+        psci::system_reset(match mode {
+            RebootMode::Normal => RebootFlags::Normal,
+            RebootMode::Bootloader => RebootFlags::Bootloader,
+            RebootMode::Recovery => RebootFlags::Recovery,
+        });
     }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    let _ = mode;
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use crate::kernel::arch::amd64::include::arch::amd64::outp;
+        use crate::kernel::dev::acpi::{self, tables::GAS_SYSTEM_IO};
+
+        // ACPI reset register first, it is the one the firmware vouches for
+        if let Some((reg, value)) = acpi::reset_register() {
+            if reg.space_id == GAS_SYSTEM_IO {
+                outp(reg.address as u16, value);
+            }
+        }
+
+        // Keyboard controller reset line
+        outp(0x64, 0xfe);
+
+        // PCI reset control register: full reset
+        outp(0xcf9, 0x02);
+        outp(0xcf9, 0x06);
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    sbi_system_reset(SBI_RESET_COLD_REBOOT);
 }
 
+/// Power the machine off, returning only if no method worked
+fn power_off() {
+    #[cfg(target_arch = "aarch64")]
+    crate::kernel::dev::psci::system_off();
+
+    #[cfg(target_arch = "x86_64")]
+    acpi_soft_off();
+
+    #[cfg(target_arch = "riscv64")]
+    sbi_system_reset(SBI_RESET_SHUTDOWN);
+}
+
+/// Enter ACPI S5 with the sleep type found in the DSDT
+#[cfg(target_arch = "x86_64")]
+fn acpi_soft_off() {
+    use crate::kernel::arch::amd64::include::arch::amd64::{inpw, outp, outpw};
+    use crate::kernel::dev::acpi::{self, dsdt};
+
+    let (fadt, s5) = match acpi::soft_off() {
+        Some(soft_off) => soft_off,
+        None => return,
+    };
+    if fadt.hw_reduced() || fadt.pm1a_control == 0 {
+        return;
+    }
+
+    unsafe {
+        let pm1a = fadt.pm1a_control as u16;
+        let pm1b = fadt.pm1b_control as u16;
+
+        // Firmware still in legacy mode ignores SLP_EN until switched
+        if inpw(pm1a) & dsdt::PM1_SCI_EN == 0 && fadt.smi_cmd != 0 {
+            outp(fadt.smi_cmd as u16, fadt.acpi_enable);
+            for _ in 0..1_000_000 {
+                if inpw(pm1a) & dsdt::PM1_SCI_EN != 0 {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+        }
+
+        if pm1b != 0 {
+            outpw(pm1b, dsdt::pm1_control(inpw(pm1b), s5.b));
+        }
+        outpw(pm1a, dsdt::pm1_control(inpw(pm1a), s5.a));
+    }
+}
+
+/// SBI system reset extension ("SRST")
+#[cfg(target_arch = "riscv64")]
+const SBI_EXT_SRST: u64 = 0x5352_5354;
+
+#[cfg(target_arch = "riscv64")]
+const SBI_RESET_SHUTDOWN: u64 = 0;
+
+#[cfg(target_arch = "riscv64")]
+const SBI_RESET_COLD_REBOOT: u64 = 1;
+
+/// Ask the SBI firmware to reset or shut down the machine
+#[cfg(target_arch = "riscv64")]
+fn sbi_system_reset(reset_type: u64) {
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") reset_type => _,
+            inlateout("a1") 0u64 => _,
+            in("a6") 0u64,
+            in("a7") SBI_EXT_SRST,
+            options(nostack),
+        );
+    }
+}
+
+/// `reboot` console command
+fn cmd_reboot(_argc: i32, _argv: &[&str], _flags: u32) -> i32 {
+    platform_halt(HALT_REASON_USER_REQUEST, HALT_ACTION_REBOOT);
+}
+
+crate::static_command!("reboot", "soft reset the machine", cmd_reboot);
+
 /// Get platform IRQ information
 pub fn platform_irq() -> u32 {
     0 // No IRQ by default
@@ -95,11 +336,8 @@ pub mod init {
     /// Platform panic start
     ///
     /// Called when the kernel panics.
-    pub fn panic_start() -> ! {
-        // TODO: Implement platform-specific panic handling
-        loop {
-            core::hint::spin_loop();
-        }
+    pub fn panic_start() {
+        super::platform_panic_start();
     }
 
     /// Get platform ramdisk (if any)
@@ -0,0 +1,372 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot configuration
+//!
+//! `\EFI\Rustux\boot.cfg` lists the entries the boot menu offers. It is
+//! plain ASCII, one `key value` pair per line; `entry` starts a new entry
+//! and the keys after it describe that entry:
+//!
+//! ```text
+//! # Seconds to wait before booting the default (0 boots at once)
+//! timeout 5
+//! # Default entry, by title or by number from 1
+//! default Rustux
+//!
+//! entry Rustux
+//!     kernel \EFI\Rustux\kernel.elf
+//!     initrd \EFI\Rustux\bootfs.img
+//!     cmdline kernel.log-level=info
+//! ```
+//!
+//! Paths are on the loader's volume and may use `/` or `\`. Entries
+//! without a kernel are dropped, and without a usable file the loader
+//! boots `\EFI\Rustux\kernel.elf` with no initrd.
+//!
+//! # Last Known Good
+//!
+//! Before entering a kernel the loader saves the chosen entry in the
+//! `RustuxBootPending` EFI variable. Once the system is up, the OS
+//! confirms the boot by moving it to `RustuxLastGood`. If the loader
+//! finds an unconfirmed boot and a last known good entry, the previous
+//! boot failed and the menu defaults to the saved entry, which still
+//! boots if `boot.cfg` itself was broken.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileInfo, FileMode, FileType};
+use uefi::runtime::{VariableAttributes, VariableVendor};
+use uefi::{cstr16, CStr16, CString16};
+
+/// Location of the configuration file
+const CONFIG_PATH: &CStr16 = cstr16!("\\EFI\\Rustux\\boot.cfg");
+
+/// Kernel booted when there is no configuration
+const DEFAULT_KERNEL: &str = "\\EFI\\Rustux\\kernel.elf";
+
+/// Most entries the menu shows
+const MAX_ENTRIES: usize = 16;
+
+/// Largest configuration file read
+const CONFIG_MAX: usize = 16 * 1024;
+
+/// Timeout without a `timeout` line, in seconds
+const DEFAULT_TIMEOUT: u32 = 5;
+
+/// Vendor GUID of the boot variables
+const BOOT_VARIABLE_VENDOR: VariableVendor =
+    VariableVendor(uefi::guid!("4e8f2a63-7c1d-4b59-9a0e-3d6b5f1c8e27"));
+
+/// Entry being booted, cleared by the OS once it is up
+const PENDING_VARIABLE: &CStr16 = cstr16!("RustuxBootPending");
+
+/// Entry that last booted successfully, written by the OS
+const LAST_GOOD_VARIABLE: &CStr16 = cstr16!("RustuxLastGood");
+
+/// One bootable entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootEntry {
+    pub title: String,
+    /// Kernel path on the loader's volume
+    pub kernel: String,
+    /// Optional initrd path, passed to the kernel as a boot module
+    pub initrd: Option<String>,
+    /// ASCII kernel command line
+    pub cmdline: String,
+}
+
+impl BootEntry {
+    fn new(title: &str) -> Self {
+        Self {
+            title: String::from(title),
+            kernel: String::new(),
+            initrd: None,
+            cmdline: String::new(),
+        }
+    }
+
+    /// The entry in configuration file syntax, for the boot variables
+    pub fn to_config(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "entry {}", self.title);
+        let _ = writeln!(text, "kernel {}", self.kernel);
+        if let Some(initrd) = &self.initrd {
+            let _ = writeln!(text, "initrd {}", initrd);
+        }
+        if !self.cmdline.is_empty() {
+            let _ = writeln!(text, "cmdline {}", self.cmdline);
+        }
+        text
+    }
+}
+
+/// Parsed boot configuration
+#[derive(Debug)]
+pub struct BootConfig {
+    /// Seconds the menu waits before booting the default
+    pub timeout: u32,
+    /// Index of the default entry
+    pub default: usize,
+    pub entries: Vec<BootEntry>,
+}
+
+impl BootConfig {
+    /// Configuration used without a usable `boot.cfg`
+    pub fn fallback() -> Self {
+        let mut entry = BootEntry::new("Rustux");
+        entry.kernel = String::from(DEFAULT_KERNEL);
+        Self {
+            timeout: 0,
+            default: 0,
+            entries: alloc::vec![entry],
+        }
+    }
+
+    /// Parse configuration text
+    ///
+    /// Unknown keys and malformed lines are skipped. Returns a
+    /// configuration without entries if the text defines none.
+    pub fn parse(text: &str) -> Self {
+        let mut timeout = DEFAULT_TIMEOUT;
+        let mut default = None;
+        let mut entries: Vec<BootEntry> = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once(|c: char| c.is_ascii_whitespace()) {
+                Some((key, value)) => (key, value.trim()),
+                None => (line, ""),
+            };
+
+            match key {
+                "timeout" => timeout = value.parse().unwrap_or(timeout),
+                "default" => default = Some(value),
+                "entry" => {
+                    if entries.len() < MAX_ENTRIES {
+                        entries.push(BootEntry::new(value));
+                    }
+                }
+                "kernel" | "initrd" | "cmdline" if !value.is_empty() => {
+                    let entry = match entries.last_mut() {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+                    match key {
+                        "kernel" => entry.kernel = volume_path(value),
+                        "initrd" => entry.initrd = Some(volume_path(value)),
+                        _ => entry.cmdline = String::from(value),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        entries.retain(|entry| !entry.kernel.is_empty());
+
+        let default = default
+            .and_then(|name| {
+                entries
+                    .iter()
+                    .position(|entry| entry.title == name)
+                    .or_else(|| name.parse::<usize>().ok()?.checked_sub(1))
+            })
+            .filter(|&index| index < entries.len())
+            .unwrap_or(0);
+
+        Self { timeout, default, entries }
+    }
+}
+
+/// Normalize a path to the backslashes the file protocol expects
+fn volume_path(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len() + 1);
+    if !path.starts_with(['/', '\\']) {
+        normalized.push('\\');
+    }
+    normalized.extend(path.chars().map(|c| if c == '/' { '\\' } else { c }));
+    normalized
+}
+
+/// Read a whole file of at most `max` bytes from the volume
+pub fn read_file(root: &mut Directory, path: &CStr16, max: usize) -> Option<Vec<u8>> {
+    let handle = root.open(path, FileMode::Read, FileAttribute::empty()).ok()?;
+    let mut file = match handle.into_type().ok()? {
+        FileType::Regular(file) => file,
+        FileType::Dir(_) => return None,
+    };
+
+    let mut info_buf = [0u8; 256];
+    let size = file.get_info::<FileInfo>(&mut info_buf).ok()?.file_size() as usize;
+    if size > max {
+        return None;
+    }
+
+    let mut data = alloc::vec![0u8; size];
+    let read = file.read(&mut data).ok()?;
+    data.truncate(read);
+    Some(data)
+}
+
+/// Load `boot.cfg`, or the fallback configuration without one
+pub fn load(root: &mut Directory) -> BootConfig {
+    let config = read_file(root, CONFIG_PATH, CONFIG_MAX)
+        .and_then(|data| String::from_utf8(data).ok())
+        .map(|text| BootConfig::parse(&text));
+
+    match config {
+        Some(config) if !config.entries.is_empty() => config,
+        _ => BootConfig::fallback(),
+    }
+}
+
+/// Convert a path to the UCS-2 string the file protocol takes
+pub fn path_ucs2(path: &str) -> Option<CString16> {
+    CString16::try_from(path).ok()
+}
+
+/// Read a boot variable holding one entry
+fn read_entry_variable(name: &CStr16) -> Option<BootEntry> {
+    let mut buf = [0u8; 2048];
+    let (data, _) = uefi::runtime::get_variable(name, &BOOT_VARIABLE_VENDOR, &mut buf).ok()?;
+    let text = core::str::from_utf8(data).ok()?;
+    BootConfig::parse(text).entries.into_iter().next()
+}
+
+/// Entry of a boot the OS never confirmed, if any
+pub fn pending_entry() -> Option<BootEntry> {
+    read_entry_variable(PENDING_VARIABLE)
+}
+
+/// Entry that last booted successfully, if any
+pub fn last_good_entry() -> Option<BootEntry> {
+    read_entry_variable(LAST_GOOD_VARIABLE)
+}
+
+/// Record the entry about to be booted
+///
+/// Failure only loses the fallback, so it is not reported.
+pub fn set_pending_entry(entry: &BootEntry) {
+    let attributes = VariableAttributes::NON_VOLATILE
+        | VariableAttributes::BOOTSERVICE_ACCESS
+        | VariableAttributes::RUNTIME_ACCESS;
+    let _ = uefi::runtime::set_variable(
+        PENDING_VARIABLE,
+        &BOOT_VARIABLE_VENDOR,
+        attributes,
+        entry.to_config().as_bytes(),
+    );
+}
+
+/// Convert UCS-2 load options to an ASCII command line
+///
+/// Non-ASCII characters are replaced with '.'.
+pub fn options_to_ascii(options: &[u8]) -> String {
+    options
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&c| c != 0)
+        .map(|c| if c < 0x80 { c as u8 as char } else { '.' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Boot the second entry after 3 seconds
+timeout 3
+default Recovery
+
+entry Rustux
+    kernel /EFI/Rustux/kernel.elf
+    initrd \\EFI\\Rustux\\bootfs.img
+    cmdline kernel.log-level=info  kernel.aslr=false
+
+entry No kernel
+    cmdline ignored
+
+entry Recovery
+\tkernel EFI/Rustux/recovery.elf
+    bogus line
+";
+
+    #[test]
+    fn test_parse() {
+        let config = BootConfig::parse(CONFIG);
+        assert_eq!(config.timeout, 3);
+        assert_eq!(config.entries.len(), 2);
+        assert_eq!(config.default, 1);
+
+        let rustux = &config.entries[0];
+        assert_eq!(rustux.title, "Rustux");
+        assert_eq!(rustux.kernel, "\\EFI\\Rustux\\kernel.elf");
+        assert_eq!(rustux.initrd.as_deref(), Some("\\EFI\\Rustux\\bootfs.img"));
+        assert_eq!(rustux.cmdline, "kernel.log-level=info  kernel.aslr=false");
+
+        let recovery = &config.entries[1];
+        assert_eq!(recovery.kernel, "\\EFI\\Rustux\\recovery.elf");
+        assert_eq!(recovery.initrd, None);
+        assert_eq!(recovery.cmdline, "");
+    }
+
+    #[test]
+    fn test_parse_default() {
+        let entries = "entry a\nkernel a\nentry b\nkernel b\n";
+        let parse = |head: &str| BootConfig::parse(&format!("{}{}", head, entries));
+
+        assert_eq!(parse("").default, 0);
+        assert_eq!(parse("default b\n").default, 1);
+        assert_eq!(parse("default 2\n").default, 1);
+        // Out of range or unknown falls back to the first entry
+        assert_eq!(parse("default 0\n").default, 0);
+        assert_eq!(parse("default 3\n").default, 0);
+        assert_eq!(parse("default c\n").default, 0);
+
+        assert_eq!(parse("").timeout, DEFAULT_TIMEOUT);
+        assert_eq!(parse("timeout soon\n").timeout, DEFAULT_TIMEOUT);
+        assert_eq!(parse("timeout 0\n").timeout, 0);
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        // Keys before the first entry belong to none
+        assert!(BootConfig::parse("kernel \\kernel.elf\n").entries.is_empty());
+
+        let mut text = String::new();
+        for i in 0..MAX_ENTRIES + 2 {
+            let _ = write!(text, "entry {}\nkernel \\{}.elf\n", i, i);
+        }
+        let config = BootConfig::parse(&text);
+        assert_eq!(config.entries.len(), MAX_ENTRIES);
+        // Keys after a dropped entry land on the last one kept
+        assert_eq!(config.entries[MAX_ENTRIES - 1].kernel, format!("\\{}.elf", MAX_ENTRIES + 1));
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        for entry in BootConfig::parse(CONFIG).entries.into_iter().chain(BootConfig::fallback().entries) {
+            let parsed = BootConfig::parse(&entry.to_config());
+            assert_eq!(parsed.entries, alloc::vec![entry]);
+        }
+    }
+
+    #[test]
+    fn test_options_to_ascii() {
+        let options: Vec<u8> = "root=/dev/sda é\0trailing"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(options_to_ascii(&options), "root=/dev/sda .");
+        assert_eq!(options_to_ascii(&[b'a', 0, b'b']), "a");
+    }
+}
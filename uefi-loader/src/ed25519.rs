@@ -0,0 +1,495 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ed25519 signature verification (RFC 8032)
+//!
+//! Only verification is implemented: everything it handles (key, message,
+//! signature) is public, so the arithmetic is plain variable-time code.
+//! Field elements are five 51-bit limbs; points use extended twisted
+//! Edwards coordinates. The group equation is checked without the
+//! cofactor, by recomputing R and comparing encodings.
+
+use crate::sha512::Sha512;
+
+/// Public key length in bytes
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Signature length in bytes
+pub const SIGNATURE_SIZE: usize = 64;
+
+const MASK51: u64 = (1 << 51) - 1;
+
+/// Element of GF(2^255 - 19)
+#[derive(Clone, Copy)]
+struct Fe([u64; 5]);
+
+/// Curve constant d = -121665/121666
+const D: [u8; 32] = [
+    0xa3, 0x78, 0x59, 0x13, 0xca, 0x4d, 0xeb, 0x75, 0xab, 0xd8, 0x41, 0x41, 0x4d, 0x0a, 0x70, 0x00,
+    0x98, 0xe8, 0x79, 0x77, 0x79, 0x40, 0xc7, 0x8c, 0x73, 0xfe, 0x6f, 0x2b, 0xee, 0x6c, 0x03, 0x52,
+];
+
+/// sqrt(-1) = 2^((p - 1) / 4)
+const SQRT_M1: [u8; 32] = [
+    0xb0, 0xa0, 0x0e, 0x4a, 0x27, 0x1b, 0xee, 0xc4, 0x78, 0xe4, 0x2f, 0xad, 0x06, 0x18, 0x43, 0x2f,
+    0xa7, 0xd7, 0xfb, 0x3d, 0x99, 0x00, 0x4d, 0x2b, 0x0b, 0xdf, 0xc1, 0x4f, 0x80, 0x24, 0x83, 0x2b,
+];
+
+/// Encoding of the base point B
+const BASE: [u8; 32] = [
+    0x58, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+    0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+];
+
+/// Group order L = 2^252 + 27742317777372353535851937790883648493, as
+/// little-endian 64-bit limbs
+const L: [u64; 4] = [0x5812631a5cf5d3ed, 0x14def9dea2f79cd6, 0, 0x1000000000000000];
+
+/// p - 2, the exponent of inversion
+const P_MINUS_2: [u8; 32] = {
+    let mut e = [0xff; 32];
+    e[0] = 0xeb;
+    e[31] = 0x7f;
+    e
+};
+
+/// (p - 5) / 8 = 2^252 - 3, the exponent of the square root
+const P_MINUS_5_DIV_8: [u8; 32] = {
+    let mut e = [0xff; 32];
+    e[0] = 0xfd;
+    e[31] = 0x0f;
+    e
+};
+
+impl Fe {
+    const ZERO: Fe = Fe([0; 5]);
+    const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+
+    /// Load 255 bits, ignoring the top bit
+    fn from_bytes(bytes: &[u8; 32]) -> Fe {
+        let load = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        Fe([
+            load(0) & MASK51,
+            (load(6) >> 3) & MASK51,
+            (load(12) >> 6) & MASK51,
+            (load(19) >> 1) & MASK51,
+            (load(24) >> 12) & MASK51,
+        ])
+    }
+
+    /// Canonical little-endian encoding
+    fn to_bytes(self) -> [u8; 32] {
+        let mut t = self.carry().0;
+
+        // Subtract p if t >= p: q is 1 exactly when t + 19 overflows 2^255
+        let mut q = (t[0] + 19) >> 51;
+        for limb in &t[1..] {
+            q = (limb + q) >> 51;
+        }
+        t[0] += 19 * q;
+        for i in 0..4 {
+            t[i + 1] += t[i] >> 51;
+            t[i] &= MASK51;
+        }
+        t[4] &= MASK51;
+
+        let mut out = [0u8; 32];
+        let mut acc: u128 = 0;
+        let mut bits = 0;
+        let mut pos = 0;
+        for limb in t {
+            acc |= (limb as u128) << bits;
+            bits += 51;
+            while bits >= 8 && pos < 32 {
+                out[pos] = acc as u8;
+                acc >>= 8;
+                bits -= 8;
+                pos += 1;
+            }
+        }
+        if pos < 32 {
+            out[pos] = acc as u8;
+        }
+        out
+    }
+
+    /// Propagate carries so every limb is below 2^52
+    fn carry(self) -> Fe {
+        let mut t = self.0;
+        for _ in 0..2 {
+            for i in 0..4 {
+                t[i + 1] += t[i] >> 51;
+                t[i] &= MASK51;
+            }
+            t[0] += 19 * (t[4] >> 51);
+            t[4] &= MASK51;
+        }
+        Fe(t)
+    }
+
+    fn add(self, other: Fe) -> Fe {
+        let mut t = self.0;
+        for (a, b) in t.iter_mut().zip(other.0) {
+            *a += b;
+        }
+        Fe(t).carry()
+    }
+
+    fn sub(self, other: Fe) -> Fe {
+        // Add 4p first so limbs never go negative
+        const FOUR_P: [u64; 5] = [
+            4 * (MASK51 - 18),
+            4 * MASK51,
+            4 * MASK51,
+            4 * MASK51,
+            4 * MASK51,
+        ];
+        let other = other.carry().0;
+        let mut t = self.carry().0;
+        for i in 0..5 {
+            t[i] = t[i] + FOUR_P[i] - other[i];
+        }
+        Fe(t).carry()
+    }
+
+    fn neg(self) -> Fe {
+        Fe::ZERO.sub(self)
+    }
+
+    fn mul(self, other: Fe) -> Fe {
+        let a = self.carry().0;
+        let b = other.carry().0;
+        let m = |x: u64, y: u64| (x as u128) * (y as u128);
+
+        // Limbs that wrap past 2^255 come back multiplied by 19
+        let b19 = [b[0], b[1] * 19, b[2] * 19, b[3] * 19, b[4] * 19];
+        let mut r = [0u128; 5];
+        for i in 0..5 {
+            for j in 0..5 {
+                if i + j < 5 {
+                    r[i + j] += m(a[i], b[j]);
+                } else {
+                    r[i + j - 5] += m(a[i], b19[j]);
+                }
+            }
+        }
+
+        let mut t = [0u64; 5];
+        let mut carry: u128 = 0;
+        for i in 0..5 {
+            let v = r[i] + carry;
+            t[i] = (v as u64) & MASK51;
+            carry = v >> 51;
+        }
+        t[0] += (carry as u64) * 19;
+        Fe(t).carry()
+    }
+
+    fn square(self) -> Fe {
+        self.mul(self)
+    }
+
+    /// Raise to a little-endian exponent
+    fn pow(self, exp: &[u8; 32]) -> Fe {
+        let mut result = Fe::ONE;
+        for i in (0..256).rev() {
+            result = result.square();
+            if (exp[i / 8] >> (i % 8)) & 1 != 0 {
+                result = result.mul(self);
+            }
+        }
+        result
+    }
+
+    fn invert(self) -> Fe {
+        self.pow(&P_MINUS_2)
+    }
+
+    fn is_zero(self) -> bool {
+        self.to_bytes() == [0; 32]
+    }
+
+    fn is_negative(self) -> bool {
+        self.to_bytes()[0] & 1 != 0
+    }
+
+    fn equals(self, other: Fe) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+/// Curve point in extended coordinates (X:Y:Z:T), x = X/Z, y = Y/Z, xy = T/Z
+#[derive(Clone, Copy)]
+struct Point {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+    t: Fe,
+}
+
+impl Point {
+    const IDENTITY: Point = Point { x: Fe::ZERO, y: Fe::ONE, z: Fe::ONE, t: Fe::ZERO };
+
+    /// Decode a point (RFC 8032 5.1.3), rejecting non-canonical y
+    fn decompress(bytes: &[u8; 32]) -> Option<Point> {
+        let sign = bytes[31] >> 7;
+        let y = Fe::from_bytes(bytes);
+        let mut canonical = *bytes;
+        canonical[31] &= 0x7f;
+        if y.to_bytes() != canonical {
+            return None;
+        }
+
+        let d = Fe::from_bytes(&D);
+        let y2 = y.square();
+        let u = y2.sub(Fe::ONE);
+        let v = d.mul(y2).add(Fe::ONE);
+
+        // x = u v^3 (u v^7)^((p - 5) / 8)
+        let v3 = v.square().mul(v);
+        let v7 = v3.square().mul(v);
+        let mut x = u.mul(v3).mul(u.mul(v7).pow(&P_MINUS_5_DIV_8));
+
+        let vx2 = v.mul(x.square());
+        if !vx2.equals(u) {
+            if vx2.equals(u.neg()) {
+                x = x.mul(Fe::from_bytes(&SQRT_M1));
+            } else {
+                return None;
+            }
+        }
+
+        if x.is_zero() && sign == 1 {
+            return None;
+        }
+        if x.is_negative() != (sign == 1) {
+            x = x.neg();
+        }
+
+        Some(Point { x, y, z: Fe::ONE, t: x.mul(y) })
+    }
+
+    fn compress(self) -> [u8; 32] {
+        let zinv = self.z.invert();
+        let x = self.x.mul(zinv);
+        let mut out = self.y.mul(zinv).to_bytes();
+        out[31] |= (x.is_negative() as u8) << 7;
+        out
+    }
+
+    /// Unified addition (RFC 8032 5.1.4), also used for doubling
+    fn add(self, other: Point) -> Point {
+        let d2 = Fe::from_bytes(&D).add(Fe::from_bytes(&D));
+        let a = self.y.sub(self.x).mul(other.y.sub(other.x));
+        let b = self.y.add(self.x).mul(other.y.add(other.x));
+        let c = self.t.mul(d2).mul(other.t);
+        let d = self.z.add(self.z).mul(other.z);
+        let e = b.sub(a);
+        let f = d.sub(c);
+        let g = d.add(c);
+        let h = b.add(a);
+        Point { x: e.mul(f), y: g.mul(h), z: f.mul(g), t: e.mul(h) }
+    }
+
+    fn neg(self) -> Point {
+        Point { x: self.x.neg(), y: self.y, z: self.z, t: self.t.neg() }
+    }
+
+    /// Multiply by a little-endian scalar
+    fn mul(self, scalar: &[u8; 32]) -> Point {
+        let mut result = Point::IDENTITY;
+        for i in (0..256).rev() {
+            result = result.add(result);
+            if (scalar[i / 8] >> (i % 8)) & 1 != 0 {
+                result = result.add(self);
+            }
+        }
+        result
+    }
+}
+
+/// Whether a little-endian 256-bit scalar is below L
+fn scalar_is_canonical(s: &[u8; 32]) -> bool {
+    for i in (0..4).rev() {
+        let limb = u64::from_le_bytes(s[i * 8..i * 8 + 8].try_into().unwrap());
+        if limb != L[i] {
+            return limb < L[i];
+        }
+    }
+    false
+}
+
+/// Reduce a little-endian 512-bit value mod L
+fn scalar_reduce(wide: &[u8; 64]) -> [u8; 32] {
+    // Shift in one bit at a time, subtracting L whenever r >= L; r stays
+    // below 2L < 2^254
+    let mut r = [0u64; 4];
+    for i in (0..512).rev() {
+        let bit = ((wide[i / 8] >> (i % 8)) & 1) as u64;
+        for j in (1..4).rev() {
+            r[j] = (r[j] << 1) | (r[j - 1] >> 63);
+        }
+        r[0] = (r[0] << 1) | bit;
+
+        let mut ge = true;
+        for j in (0..4).rev() {
+            if r[j] != L[j] {
+                ge = r[j] > L[j];
+                break;
+            }
+        }
+        if ge {
+            let mut borrow = 0u64;
+            for j in 0..4 {
+                let (v, b1) = r[j].overflowing_sub(L[j]);
+                let (v, b2) = v.overflowing_sub(borrow);
+                r[j] = v;
+                borrow = (b1 | b2) as u64;
+            }
+        }
+    }
+
+    let mut out = [0u8; 32];
+    for (chunk, limb) in out.chunks_exact_mut(8).zip(r) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    out
+}
+
+/// Verify an Ed25519 `signature` over `message` with `public_key`
+pub fn verify(
+    public_key: &[u8; PUBLIC_KEY_SIZE],
+    signature: &[u8; SIGNATURE_SIZE],
+    message: &[u8],
+) -> bool {
+    let r: &[u8; 32] = signature[..32].try_into().unwrap();
+    let s: &[u8; 32] = signature[32..].try_into().unwrap();
+    if !scalar_is_canonical(s) {
+        return false;
+    }
+
+    let a = match Point::decompress(public_key) {
+        Some(a) => a,
+        None => return false,
+    };
+    let base = match Point::decompress(&BASE) {
+        Some(base) => base,
+        None => return false,
+    };
+
+    let mut hasher = Sha512::new();
+    hasher.update(r);
+    hasher.update(public_key);
+    hasher.update(message);
+    let k = scalar_reduce(&hasher.finalize());
+
+    // R == [S]B - [k]A
+    base.mul(s).add(a.neg().mul(&k)).compress() == *r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex<const N: usize>(text: &str) -> [u8; N] {
+        let mut out = [0u8; N];
+        for (byte, pair) in out.iter_mut().zip(text.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap();
+        }
+        out
+    }
+
+    /// RFC 8032 section 7.1 tests 1 to 3: public key, message, signature
+    const VECTORS: [(&str, &[u8], &str); 3] = [
+        (
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            b"",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            &[0x72],
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            &[0xaf, 0x82],
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+             18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+    ];
+
+    #[test]
+    fn test_rfc8032_vectors() {
+        for (key, message, signature) in VECTORS {
+            assert!(verify(&hex(key), &hex(signature), message));
+        }
+    }
+
+    #[test]
+    fn test_rejects_tampering() {
+        let (key, message, signature) = VECTORS[2];
+        let (key, signature): ([u8; 32], [u8; 64]) = (hex(key), hex(signature));
+
+        assert!(!verify(&key, &signature, &[0xaf, 0x83]));
+        assert!(!verify(&key, &signature, &[0xaf]));
+        assert!(!verify(&hex(VECTORS[1].0), &signature, message));
+        for byte in [0, 31, 32, 63] {
+            let mut tampered = signature;
+            tampered[byte] ^= 1;
+            assert!(!verify(&key, &tampered, message), "byte {}", byte);
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_s() {
+        let (key, message, signature) = VECTORS[0];
+        let mut signature: [u8; 64] = hex(signature);
+        signature[32..].fill(0xff);
+        assert!(!verify(&hex(key), &signature, message));
+
+        let l_minus_1 = hex("ecd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010");
+        assert!(scalar_is_canonical(&l_minus_1));
+        let mut l = l_minus_1;
+        l[0] += 1;
+        assert!(!scalar_is_canonical(&l));
+    }
+
+    #[test]
+    fn test_scalar_reduce() {
+        assert_eq!(
+            scalar_reduce(&[0xff; 64]),
+            hex("000f9c44e31106a447938568a71b0ed065bef517d273ecce3d9a307c1b419903")
+        );
+        let mut small = [0u8; 64];
+        small[0] = 42;
+        assert_eq!(scalar_reduce(&small)[..2], [42, 0]);
+    }
+
+    #[test]
+    fn test_decompress() {
+        assert!(Point::decompress(&BASE).is_some());
+        assert_eq!(Point::decompress(&BASE).unwrap().compress(), BASE);
+
+        // y = 2 is not on the curve
+        let mut y2 = [0u8; 32];
+        y2[0] = 2;
+        assert!(Point::decompress(&y2).is_none());
+
+        // y = p, an unreduced encoding of 0
+        let p: [u8; 32] = hex("edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f");
+        assert!(Point::decompress(&p).is_none());
+
+        // y = 1 has x = 0, which has no negative
+        let mut y1 = [0u8; 32];
+        y1[0] = 1;
+        assert!(Point::decompress(&y1).is_some());
+        y1[31] |= 0x80;
+        assert!(Point::decompress(&y1).is_none());
+    }
+}
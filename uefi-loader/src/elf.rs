@@ -0,0 +1,631 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ELF kernel image loading
+//!
+//! The kernel is an ELF64 image. Its PT_LOAD segments are copied into one
+//! physically contiguous allocation, keeping their relative layout, and
+//! the image is later mapped at its virtual address by `paging`.
+//!
+//! An image with a RELA table in PT_DYNAMIC (ET_DYN, or an executable
+//! linked with `-pie`) is relocatable: it may be placed anywhere, and it
+//! then runs at its linked address plus the distance it was moved in
+//! physical memory. Only R_X86_64_RELATIVE relocations are supported, as
+//! a kernel has no symbols to resolve. Other images are loaded at the
+//! physical address of their first segment or not at all.
+//!
+//! The loader enters the `kmain` symbol when the image has a symbol
+//! table, and the ELF entry point otherwise.
+
+use uefi::boot::{AllocateType, MemoryType};
+
+use crate::kaslr;
+
+/// `e_ident` fields
+const ELFMAG: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+/// `e_type` values
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+/// `e_machine` for x86-64
+const EM_X86_64: u16 = 62;
+
+/// Program header types and flags
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PF_W: u32 = 2;
+
+/// Section header type of the static symbol table
+const SHT_SYMTAB: u32 = 2;
+
+/// Dynamic tags
+const DT_NULL: i64 = 0;
+const DT_RELA: i64 = 7;
+const DT_RELASZ: i64 = 8;
+const DT_RELAENT: i64 = 9;
+
+/// Relocation types
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_RELATIVE: u32 = 8;
+
+/// Symbol the loader enters
+const ENTRY_SYMBOL: &[u8] = b"kmain";
+
+/// Most PT_LOAD segments a kernel image may have
+pub const MAX_SEGMENTS: usize = 8;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// ELF64 file header
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+/// ELF64 program header
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+/// ELF64 section header
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Shdr {
+    sh_name: u32,
+    sh_type: u32,
+    sh_flags: u64,
+    sh_addr: u64,
+    sh_offset: u64,
+    sh_size: u64,
+    sh_link: u32,
+    sh_info: u32,
+    sh_addralign: u64,
+    sh_entsize: u64,
+}
+
+/// ELF64 symbol
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Sym {
+    st_name: u32,
+    st_info: u8,
+    st_other: u8,
+    st_shndx: u16,
+    st_value: u64,
+    st_size: u64,
+}
+
+/// ELF64 dynamic table entry
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Dyn {
+    d_tag: i64,
+    d_val: u64,
+}
+
+/// ELF64 relocation with addend
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Rela {
+    r_offset: u64,
+    r_info: u64,
+    r_addend: i64,
+}
+
+/// One loaded segment, as the page tables need it
+#[derive(Clone, Copy)]
+pub struct Segment {
+    /// Virtual address the segment runs at (page aligned)
+    pub virt: u64,
+    /// Physical address it was loaded at (page aligned)
+    pub phys: u64,
+    /// Size in bytes (page aligned)
+    pub size: u64,
+    pub writable: bool,
+}
+
+impl Segment {
+    const EMPTY: Self = Self { virt: 0, phys: 0, size: 0, writable: false };
+}
+
+/// A kernel image loaded and relocated by the loader
+pub struct LoadedKernel {
+    /// Physical base the image was loaded at
+    pub base: u64,
+    /// Size of the image in memory
+    pub size: u64,
+    /// Virtual address of the entry point
+    pub entry: u64,
+    /// Distance from the linked virtual address to `virt_base`
+    pub slide: u64,
+    /// Number of valid entries in `segments`
+    pub segment_count: usize,
+    pub segments: [Segment; MAX_SEGMENTS],
+}
+
+impl LoadedKernel {
+    /// The loaded segments
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments[..self.segment_count]
+    }
+}
+
+/// Read a structure at `offset`, checking it lies within `data`
+fn read<T: Copy>(data: &[u8], offset: u64) -> Result<T, &'static str> {
+    let size = core::mem::size_of::<T>() as u64;
+    match offset.checked_add(size) {
+        Some(end) if end <= data.len() as u64 => {}
+        _ => return Err("ELF structure out of bounds"),
+    }
+    Ok(unsafe { (data.as_ptr().add(offset as usize) as *const T).read_unaligned() })
+}
+
+/// Read program header `index`
+fn program_header(data: &[u8], ehdr: &Elf64Ehdr, index: usize) -> Result<Elf64Phdr, &'static str> {
+    read(data, ehdr.e_phoff + (index * ehdr.e_phentsize as usize) as u64)
+}
+
+/// Validate the ELF header of a kernel image
+pub fn validate(data: &[u8]) -> Result<(), &'static str> {
+    let ehdr: Elf64Ehdr = read(data, 0).map_err(|_| "File too small for ELF header")?;
+
+    if ehdr.e_ident[..4] != ELFMAG {
+        return Err("Not an ELF file");
+    }
+    if ehdr.e_ident[4] != ELFCLASS64 || ehdr.e_ident[5] != ELFDATA2LSB {
+        return Err("Not a little-endian ELF64 file");
+    }
+    if ehdr.e_type != ET_EXEC && ehdr.e_type != ET_DYN {
+        return Err("Not an executable ELF file");
+    }
+    if ehdr.e_machine != EM_X86_64 {
+        return Err("Wrong machine type (not x86-64)");
+    }
+    if (ehdr.e_phentsize as usize) < core::mem::size_of::<Elf64Phdr>() || ehdr.e_phnum == 0 {
+        return Err("Invalid program header table");
+    }
+
+    Ok(())
+}
+
+/// Find `kmain` in the static symbol table
+fn find_entry_symbol(data: &[u8], ehdr: &Elf64Ehdr) -> Option<u64> {
+    if ehdr.e_shoff == 0 || (ehdr.e_shentsize as usize) < core::mem::size_of::<Elf64Shdr>() {
+        return None;
+    }
+
+    for i in 0..ehdr.e_shnum as u64 {
+        let shdr: Elf64Shdr = read(data, ehdr.e_shoff + i * ehdr.e_shentsize as u64).ok()?;
+        if shdr.sh_type != SHT_SYMTAB || shdr.sh_entsize == 0 {
+            continue;
+        }
+
+        let strtab: Elf64Shdr =
+            read(data, ehdr.e_shoff + shdr.sh_link as u64 * ehdr.e_shentsize as u64).ok()?;
+        let strings = data.get(strtab.sh_offset as usize..strtab.sh_offset.saturating_add(strtab.sh_size) as usize)?;
+
+        for j in 0..shdr.sh_size / shdr.sh_entsize {
+            let sym: Elf64Sym = read(data, shdr.sh_offset + j * shdr.sh_entsize).ok()?;
+            let name = match strings.get(sym.st_name as usize..) {
+                Some(name) => name,
+                None => continue,
+            };
+            if sym.st_value != 0
+                && name.starts_with(ENTRY_SYMBOL)
+                && name.get(ENTRY_SYMBOL.len()) == Some(&0)
+            {
+                return Some(sym.st_value);
+            }
+        }
+    }
+
+    None
+}
+
+/// Image offset, size and entry size of the RELA table, from PT_DYNAMIC
+fn rela_table(
+    data: &[u8],
+    dynamic: &Elf64Phdr,
+    link_base: u64,
+) -> Result<Option<(u64, u64, u64)>, &'static str> {
+    let mut rela = None;
+    let mut relasz = 0;
+    let mut relaent = core::mem::size_of::<Elf64Rela>() as u64;
+
+    let count = dynamic.p_filesz / core::mem::size_of::<Elf64Dyn>() as u64;
+    for i in 0..count {
+        let entry: Elf64Dyn = read(data, dynamic.p_offset + i * core::mem::size_of::<Elf64Dyn>() as u64)?;
+        match entry.d_tag {
+            DT_NULL => break,
+            DT_RELA => rela = Some(entry.d_val.wrapping_sub(link_base)),
+            DT_RELASZ => relasz = entry.d_val,
+            DT_RELAENT => relaent = entry.d_val,
+            _ => {}
+        }
+    }
+
+    match rela {
+        Some(offset) if relasz != 0 => {
+            if relaent < core::mem::size_of::<Elf64Rela>() as u64 {
+                return Err("Invalid relocation entry size");
+            }
+            Ok(Some((offset, relasz, relaent)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Apply R_X86_64_RELATIVE relocations to the loaded image
+fn relocate(
+    image: &mut [u8],
+    (offset, size, entsize): (u64, u64, u64),
+    link_base: u64,
+    slide: u64,
+) -> Result<(), &'static str> {
+    for i in 0..size / entsize {
+        let rela: Elf64Rela = read(image, offset.wrapping_add(i * entsize))?;
+        match (rela.r_info & 0xffff_ffff) as u32 {
+            R_X86_64_NONE => {}
+            R_X86_64_RELATIVE => {
+                let target = rela.r_offset.wrapping_sub(link_base);
+                match target.checked_add(8) {
+                    Some(end) if end <= image.len() as u64 => {}
+                    _ => return Err("Relocation target out of bounds"),
+                }
+                let value = (rela.r_addend as u64).wrapping_add(slide);
+                image[target as usize..target as usize + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            _ => return Err("Unsupported relocation type"),
+        }
+    }
+    Ok(())
+}
+
+/// Load a validated ELF kernel image into its own pages
+///
+/// With `kaslr_seed`, a relocatable image is placed at a random address
+/// picked from the seed (see `kaslr`).
+pub fn load(data: &[u8], kaslr_seed: Option<u64>) -> Result<LoadedKernel, &'static str> {
+    let ehdr: Elf64Ehdr = read(data, 0)?;
+
+    // Extent of the image, and where it asks to be loaded
+    let mut link_base = u64::MAX;
+    let mut link_end = 0u64;
+    let mut phys_base = 0u64;
+    let mut dynamic = None;
+    for i in 0..ehdr.e_phnum as usize {
+        let phdr = program_header(data, &ehdr, i)?;
+        match phdr.p_type {
+            PT_LOAD if phdr.p_memsz != 0 => {
+                if phdr.p_filesz > phdr.p_memsz {
+                    return Err("Invalid segment size");
+                }
+                let start = phdr.p_vaddr & !(PAGE_SIZE - 1);
+                if start < link_base {
+                    link_base = start;
+                    phys_base = phdr.p_paddr & !(PAGE_SIZE - 1);
+                }
+                let end = phdr.p_vaddr.checked_add(phdr.p_memsz).ok_or("Segment out of bounds")?;
+                link_end = link_end.max(end);
+            }
+            PT_DYNAMIC => dynamic = Some(phdr),
+            _ => {}
+        }
+    }
+    if link_end == 0 {
+        return Err("No loadable segments");
+    }
+
+    let image_size = (link_end - link_base + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let pages = (image_size / PAGE_SIZE) as usize;
+    let relocations = match dynamic {
+        Some(dynamic) => rela_table(data, &dynamic, link_base)?,
+        None => None,
+    };
+    let relocatable = relocations.is_some();
+
+    // A relocatable image may go anywhere, randomized if asked; others
+    // only at their physical address. Page 0 is never a load address.
+    let randomized = kaslr_seed
+        .filter(|_| relocatable)
+        .and_then(|seed| kaslr::allocate_random(pages, seed));
+    let preferred = || {
+        if phys_base == 0 {
+            return None;
+        }
+        uefi::boot::allocate_pages(AllocateType::Address(phys_base), MemoryType::LOADER_CODE, pages)
+            .ok()
+            .map(|ptr| ptr.as_ptr())
+    };
+    let image_ptr = match randomized.or_else(preferred) {
+        Some(ptr) => ptr,
+        None if relocatable => {
+            uefi::boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_CODE, pages)
+                .map_err(|_| "Failed to allocate kernel image")?
+                .as_ptr()
+        }
+        None => return Err("Kernel load address in use and image is not relocatable"),
+    };
+
+    let base = image_ptr as u64;
+    let image = unsafe { core::slice::from_raw_parts_mut(image_ptr, image_size as usize) };
+
+    // Zero the whole image (covers .bss and segment padding)
+    image.fill(0);
+
+    let mut segments = [Segment::EMPTY; MAX_SEGMENTS];
+    let mut segment_count = 0;
+    for i in 0..ehdr.e_phnum as usize {
+        let phdr = program_header(data, &ehdr, i)?;
+        if phdr.p_type != PT_LOAD || phdr.p_memsz == 0 {
+            continue;
+        }
+
+        let offset = (phdr.p_vaddr - link_base) as usize;
+        let file = data
+            .get(phdr.p_offset as usize..phdr.p_offset.saturating_add(phdr.p_filesz) as usize)
+            .ok_or("Segment out of bounds")?;
+        image[offset..offset + file.len()].copy_from_slice(file);
+
+        if segment_count == MAX_SEGMENTS {
+            return Err("Too many segments");
+        }
+        let start = phdr.p_vaddr & !(PAGE_SIZE - 1);
+        let end = (phdr.p_vaddr + phdr.p_memsz + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        segments[segment_count] = Segment {
+            virt: start,
+            phys: base + (start - link_base),
+            size: end - start,
+            writable: phdr.p_flags & PF_W != 0,
+        };
+        segment_count += 1;
+    }
+
+    // A moved image runs moved by the same distance in virtual memory
+    let slide = if relocatable { base.wrapping_sub(phys_base) } else { 0 };
+    if let Some(table) = relocations {
+        relocate(image, table, link_base, slide)?;
+    }
+    for segment in &mut segments[..segment_count] {
+        segment.virt = segment.virt.wrapping_add(slide);
+    }
+
+    let entry = find_entry_symbol(data, &ehdr).unwrap_or(ehdr.e_entry);
+    if entry < link_base || entry >= link_end {
+        return Err("Entry point outside the image");
+    }
+
+    Ok(LoadedKernel {
+        base,
+        size: image_size,
+        entry: entry.wrapping_add(slide),
+        slide,
+        segment_count,
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    const LINK_BASE: u64 = 0xFFFF_FFFF_8000_0000;
+
+    /// Write `value` at `offset` in `buf`, growing it as needed
+    fn put<T: Copy>(buf: &mut Vec<u8>, offset: usize, value: T) {
+        let size = core::mem::size_of::<T>();
+        if buf.len() < offset + size {
+            buf.resize(offset + size, 0);
+        }
+        let bytes = unsafe { core::slice::from_raw_parts(&value as *const T as *const u8, size) };
+        buf[offset..offset + size].copy_from_slice(bytes);
+    }
+
+    fn ehdr() -> Elf64Ehdr {
+        let mut e_ident = [0u8; 16];
+        e_ident[..4].copy_from_slice(&ELFMAG);
+        e_ident[4] = ELFCLASS64;
+        e_ident[5] = ELFDATA2LSB;
+        Elf64Ehdr {
+            e_ident,
+            e_type: ET_EXEC,
+            e_machine: EM_X86_64,
+            e_version: 1,
+            e_entry: LINK_BASE,
+            e_phoff: 64,
+            e_shoff: 0,
+            e_flags: 0,
+            e_ehsize: 64,
+            e_phentsize: core::mem::size_of::<Elf64Phdr>() as u16,
+            e_phnum: 1,
+            e_shentsize: core::mem::size_of::<Elf64Shdr>() as u16,
+            e_shnum: 0,
+            e_shstrndx: 0,
+        }
+    }
+
+    fn shdr(sh_type: u32, sh_offset: u64, sh_size: u64, sh_link: u32, sh_entsize: u64) -> Elf64Shdr {
+        Elf64Shdr {
+            sh_name: 0,
+            sh_type,
+            sh_flags: 0,
+            sh_addr: 0,
+            sh_offset,
+            sh_size,
+            sh_link,
+            sh_info: 0,
+            sh_addralign: 0,
+            sh_entsize,
+        }
+    }
+
+    fn sym(st_name: u32, st_value: u64) -> Elf64Sym {
+        Elf64Sym { st_name, st_info: 0, st_other: 0, st_shndx: 0, st_value, st_size: 0 }
+    }
+
+    fn rela(r_offset: u64, r_type: u32, r_addend: i64) -> Elf64Rela {
+        Elf64Rela { r_offset, r_info: r_type as u64, r_addend }
+    }
+
+    fn validate_with(edit: impl FnOnce(&mut Elf64Ehdr)) -> Result<(), &'static str> {
+        let mut header = ehdr();
+        edit(&mut header);
+        let mut data = Vec::new();
+        put(&mut data, 0, header);
+        validate(&data)
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate_with(|_| {}), Ok(()));
+        assert_eq!(validate_with(|e| e.e_type = ET_DYN), Ok(()));
+
+        assert_eq!(validate_with(|e| e.e_ident[0] = 0), Err("Not an ELF file"));
+        assert_eq!(validate_with(|e| e.e_ident[4] = 1), Err("Not a little-endian ELF64 file"));
+        assert_eq!(validate_with(|e| e.e_type = 1), Err("Not an executable ELF file"));
+        assert_eq!(validate_with(|e| e.e_machine = 183), Err("Wrong machine type (not x86-64)"));
+        assert_eq!(validate_with(|e| e.e_phnum = 0), Err("Invalid program header table"));
+        assert_eq!(validate_with(|e| e.e_phentsize = 32), Err("Invalid program header table"));
+
+        assert_eq!(validate(&[0x7f, b'E', b'L', b'F']), Err("File too small for ELF header"));
+    }
+
+    /// An image whose symbol table has `kmain_helper` and then `kmain`
+    /// at `kmain`
+    fn image_with_symbols(kmain: u64) -> (Vec<u8>, Elf64Ehdr) {
+        let mut header = ehdr();
+        header.e_shoff = 64;
+        header.e_shnum = 3;
+
+        let strings = b"\0kmain_helper\0kmain\0";
+        let mut data = Vec::new();
+        put(&mut data, 0, header);
+        put(&mut data, 64, shdr(0, 0, 0, 0, 0));
+        put(&mut data, 128, shdr(SHT_SYMTAB, 256, 3 * 24, 2, 24));
+        put(&mut data, 192, shdr(3, 328, strings.len() as u64, 0, 0));
+        put(&mut data, 256, sym(0, 0));
+        put(&mut data, 280, sym(1, LINK_BASE + 0x1000));
+        put(&mut data, 304, sym(14, kmain));
+        put(&mut data, 328, *strings);
+        (data, header)
+    }
+
+    #[test]
+    fn test_find_entry_symbol() {
+        // A symbol that only starts with `kmain` isn't it
+        let (data, header) = image_with_symbols(LINK_BASE + 0x2000);
+        assert_eq!(find_entry_symbol(&data, &header), Some(LINK_BASE + 0x2000));
+
+        // Undefined, or no section headers at all
+        let (data, header) = image_with_symbols(0);
+        assert_eq!(find_entry_symbol(&data, &header), None);
+        assert_eq!(find_entry_symbol(&data, &ehdr()), None);
+    }
+
+    fn dynamic(entries: &[(i64, u64)]) -> (Vec<u8>, Elf64Phdr) {
+        let mut data = Vec::new();
+        for (i, &(d_tag, d_val)) in entries.iter().enumerate() {
+            put(&mut data, i * 16, Elf64Dyn { d_tag, d_val });
+        }
+        let phdr = Elf64Phdr {
+            p_type: PT_DYNAMIC,
+            p_flags: 0,
+            p_offset: 0,
+            p_vaddr: 0,
+            p_paddr: 0,
+            p_filesz: data.len() as u64,
+            p_memsz: data.len() as u64,
+            p_align: 8,
+        };
+        (data, phdr)
+    }
+
+    #[test]
+    fn test_rela_table() {
+        let (data, phdr) = dynamic(&[
+            (DT_RELA, LINK_BASE + 0x80),
+            (DT_RELASZ, 48),
+            (DT_RELAENT, 24),
+            (DT_NULL, 0),
+            // Past DT_NULL, so ignored
+            (DT_RELASZ, 96),
+        ]);
+        assert_eq!(rela_table(&data, &phdr, LINK_BASE), Ok(Some((0x80, 48, 24))));
+
+        // No table, or an empty one
+        let (data, phdr) = dynamic(&[(DT_RELASZ, 48), (DT_NULL, 0)]);
+        assert_eq!(rela_table(&data, &phdr, LINK_BASE), Ok(None));
+        let (data, phdr) = dynamic(&[(DT_RELA, LINK_BASE + 0x80), (DT_NULL, 0)]);
+        assert_eq!(rela_table(&data, &phdr, LINK_BASE), Ok(None));
+
+        let (data, phdr) = dynamic(&[(DT_RELA, LINK_BASE), (DT_RELASZ, 48), (DT_RELAENT, 8)]);
+        assert_eq!(rela_table(&data, &phdr, LINK_BASE), Err("Invalid relocation entry size"));
+    }
+
+    /// A 256-byte image with `relas` at offset 0x80
+    fn image_with_relas(relas: &[Elf64Rela]) -> (Vec<u8>, (u64, u64, u64)) {
+        let mut image = vec![0u8; 0x100];
+        for (i, &entry) in relas.iter().enumerate() {
+            put(&mut image, 0x80 + i * 24, entry);
+        }
+        (image, (0x80, relas.len() as u64 * 24, 24))
+    }
+
+    #[test]
+    fn test_relocate() {
+        let slide = 0x40_0000;
+        let (mut image, table) = image_with_relas(&[
+            rela(LINK_BASE + 0x10, R_X86_64_RELATIVE, (LINK_BASE + 0x1234) as i64),
+            rela(0, R_X86_64_NONE, 0),
+            rela(LINK_BASE + 0x20, R_X86_64_RELATIVE, LINK_BASE as i64),
+        ]);
+        assert_eq!(relocate(&mut image, table, LINK_BASE, slide), Ok(()));
+        assert_eq!(image[0x10..0x18], (LINK_BASE + 0x1234 + slide).to_le_bytes());
+        assert_eq!(image[0x20..0x28], (LINK_BASE + slide).to_le_bytes());
+        assert_eq!(image[0x18..0x20], [0; 8]);
+    }
+
+    #[test]
+    fn test_relocate_rejects_bad_entries() {
+        // R_X86_64_64 needs a symbol
+        let (mut image, table) = image_with_relas(&[rela(LINK_BASE, 1, 0)]);
+        assert_eq!(relocate(&mut image, table, LINK_BASE, 0), Err("Unsupported relocation type"));
+
+        // The last 8 bytes fit; one byte further doesn't
+        let (mut image, table) = image_with_relas(&[rela(LINK_BASE + 0xF8, R_X86_64_RELATIVE, 0)]);
+        assert_eq!(relocate(&mut image, table, LINK_BASE, 0), Ok(()));
+        let (mut image, table) = image_with_relas(&[rela(LINK_BASE + 0xF9, R_X86_64_RELATIVE, 0)]);
+        assert_eq!(relocate(&mut image, table, LINK_BASE, 0), Err("Relocation target out of bounds"));
+    }
+}
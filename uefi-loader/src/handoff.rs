@@ -0,0 +1,686 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Handoff - Zircon-style boot information block
+//!
+//! The loader builds a single, physically-contiguous `KernelHandoff`
+//! structure before calling ExitBootServices. Everything the kernel needs
+//! to know about the platform lives inline in this block (no pointers into
+//! boot-services memory), so it stays valid after the firmware is gone.
+//!
+//! The layout is mirrored by `kernel::handoff` and must be kept in sync.
+
+use uefi::boot::{AllocateType, MemoryType};
+use uefi::Status;
+
+// ============================================================================
+// Handoff ABI
+// ============================================================================
+
+/// Handoff magic ("RXHANDOF")
+pub const HANDOFF_MAGIC: u64 = 0x464F_444E_4148_5852;
+
+/// Handoff layout version - bump when the structure changes
+pub const HANDOFF_VERSION: u32 = 7;
+
+/// Maximum number of memory ranges passed to the kernel
+pub const HANDOFF_MAX_MEMORY_RANGES: usize = 256;
+
+/// Maximum kernel command line length (including NUL)
+pub const HANDOFF_CMDLINE_MAX: usize = 1024;
+
+/// Maximum number of boot modules
+pub const HANDOFF_MAX_MODULES: usize = 16;
+
+/// Maximum module name length (including NUL)
+pub const HANDOFF_MODULE_NAME_MAX: usize = 48;
+
+/// Size of the boot RNG seed buffer
+pub const HANDOFF_RNG_SEED_MAX: usize = 32;
+
+/// Maximum number of EFI runtime regions passed to the kernel
+pub const HANDOFF_MAX_EFI_RUNTIME: usize = 32;
+
+/// `boot_flags`: the firmware enforces UEFI Secure Boot
+pub const HANDOFF_FLAG_SECURE_BOOT: u32 = 1 << 0;
+
+/// `boot_flags`: the kernel and boot modules passed signature verification
+pub const HANDOFF_FLAG_VERIFIED: u32 = 1 << 1;
+
+/// `boot_flags`: the kernel, initrd and command line were measured into
+/// the TPM
+pub const HANDOFF_FLAG_MEASURED: u32 = 1 << 2;
+
+/// Rustux memory type for kernel handoff
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RustuxMemoryType {
+    Available = 1,
+    Reserved = 2,
+    Reclaimable = 3,
+    Peripheral = 4,
+    Persistent = 5,
+}
+
+impl From<MemoryType> for RustuxMemoryType {
+    fn from(efi_type: MemoryType) -> Self {
+        match efi_type {
+            MemoryType::LOADER_CODE
+            | MemoryType::LOADER_DATA
+            | MemoryType::BOOT_SERVICES_CODE
+            | MemoryType::BOOT_SERVICES_DATA
+            | MemoryType::CONVENTIONAL => RustuxMemoryType::Available,
+
+            MemoryType::MMIO
+            | MemoryType::MMIO_PORT_SPACE => RustuxMemoryType::Peripheral,
+
+            MemoryType::ACPI_RECLAIM
+            | MemoryType::ACPI_NON_VOLATILE => RustuxMemoryType::Reclaimable,
+
+            CRASHLOG_MEMORY_TYPE => RustuxMemoryType::Persistent,
+
+            _ => RustuxMemoryType::Reserved,
+        }
+    }
+}
+
+/// EFI memory type of the crash log region (OS loader defined range)
+pub const CRASHLOG_MEMORY_TYPE: MemoryType = MemoryType::custom(0x8000_434C);
+
+/// Fixed physical address of the crash log region
+///
+/// The address must be the same on every boot for the kernel to find the
+/// previous boot's log. It sits below the KASLR window.
+pub const CRASHLOG_BASE: u64 = 0x00F0_0000;
+
+/// Size of the crash log region in pages
+pub const CRASHLOG_PAGES: usize = 16;
+
+/// Reserve the crash log region before anything else can claim it
+///
+/// The pages are not cleared: they may hold the log of a panic before a
+/// warm reboot, which the kernel recovers. Returns false if firmware
+/// already uses the address, in which case crashes are not kept.
+pub fn reserve_crashlog() -> bool {
+    uefi::boot::allocate_pages(
+        AllocateType::Address(CRASHLOG_BASE),
+        CRASHLOG_MEMORY_TYPE,
+        CRASHLOG_PAGES,
+    )
+    .is_ok()
+}
+
+/// Memory range descriptor for kernel handoff
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryRange {
+    pub base: u64,
+    pub length: u64,
+    pub mem_type: RustuxMemoryType,
+    pub reserved: u32,
+}
+
+impl MemoryRange {
+    const EMPTY: Self = Self {
+        base: 0,
+        length: 0,
+        mem_type: RustuxMemoryType::Reserved,
+        reserved: 0,
+    };
+}
+
+/// EFI memory map attribute: the region is used by runtime services
+const EFI_MEMORY_RUNTIME: u64 = 1 << 63;
+
+/// Region of the EFI memory map that runtime services need
+///
+/// Kept with its EFI type and attributes, which the plain memory map
+/// loses, so the kernel can map it and pass it to SetVirtualAddressMap.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct EfiRuntimeRange {
+    pub phys_start: u64,
+    pub page_count: u64,
+    /// EFI_MEMORY_* attribute bits
+    pub attribute: u64,
+    /// EFI memory type
+    pub efi_type: u32,
+    pub reserved: u32,
+}
+
+impl EfiRuntimeRange {
+    const EMPTY: Self = Self {
+        phys_start: 0,
+        page_count: 0,
+        attribute: 0,
+        efi_type: 0,
+        reserved: 0,
+    };
+}
+
+/// Framebuffer pixel format
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferFormat {
+    None = 0,
+    RGB = 1,
+    BGR = 2,
+}
+
+/// Framebuffer description
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FramebufferInfo {
+    pub base: u64,
+    pub size: u64,
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub format: FramebufferFormat,
+}
+
+impl FramebufferInfo {
+    const EMPTY: Self = Self {
+        base: 0,
+        size: 0,
+        width: 0,
+        height: 0,
+        stride: 0,
+        format: FramebufferFormat::None,
+    };
+}
+
+/// Boot module loaded alongside the kernel
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BootModule {
+    pub base: u64,
+    pub length: u64,
+    /// NUL-terminated module name
+    pub name: [u8; HANDOFF_MODULE_NAME_MAX],
+}
+
+impl BootModule {
+    pub const EMPTY: Self = Self {
+        base: 0,
+        length: 0,
+        name: [0; HANDOFF_MODULE_NAME_MAX],
+    };
+}
+
+/// Kernel handoff structure - passed to kernel on boot
+///
+/// Optional values use 0 to mean "not present".
+#[repr(C)]
+pub struct KernelHandoff {
+    /// Must be `HANDOFF_MAGIC`
+    pub magic: u64,
+    /// Must be `HANDOFF_VERSION`
+    pub version: u32,
+    /// Size of this structure in bytes
+    pub size: u32,
+
+    /// Physical address of the ACPI RSDP (0 if absent)
+    pub acpi_rsdp: u64,
+    /// Physical address of the SMBIOS entry point (0 if absent)
+    pub smbios_entry: u64,
+    /// EFI system table (runtime services remain usable)
+    pub system_table: u64,
+
+    /// Physical load address of the kernel image
+    pub kernel_base: u64,
+    /// Size of the loaded kernel image in bytes
+    pub kernel_size: u64,
+
+    /// Framebuffer (format == None if absent)
+    pub framebuffer: FramebufferInfo,
+
+    /// Number of valid entries in `memory_map`
+    pub memory_range_count: u32,
+    /// Length of `cmdline` excluding the NUL terminator
+    pub cmdline_len: u32,
+    pub memory_map: [MemoryRange; HANDOFF_MAX_MEMORY_RANGES],
+
+    /// NUL-terminated ASCII kernel command line
+    pub cmdline: [u8; HANDOFF_CMDLINE_MAX],
+
+    /// Number of valid entries in `modules`
+    pub module_count: u32,
+    pub reserved0: u32,
+    pub modules: [BootModule; HANDOFF_MAX_MODULES],
+
+    /// Physical address of the flattened device tree (0 if absent)
+    pub devicetree: u64,
+
+    /// Distance the kernel was loaded from its linked base (0 if not moved)
+    pub kernel_slide: u64,
+    /// Number of valid bytes in `rng_seed`
+    pub rng_seed_len: u32,
+    /// `HANDOFF_FLAG_*` bits describing how the kernel was booted
+    pub boot_flags: u32,
+    /// Random bytes from the firmware RNG or RDRAND
+    pub rng_seed: [u8; HANDOFF_RNG_SEED_MAX],
+
+    /// Physical address of the TPM event log copy (0 if absent)
+    pub tpm_event_log: u64,
+    /// Length of the event log copy in bytes
+    pub tpm_event_log_size: u64,
+    /// Physical address of the EFI_TCG2_FINAL_EVENTS_TABLE (0 if absent)
+    pub tpm_final_events: u64,
+
+    /// Number of valid entries in `efi_runtime`
+    pub efi_runtime_count: u32,
+    pub reserved1: u32,
+    /// Memory map entries with EFI_MEMORY_RUNTIME set
+    pub efi_runtime: [EfiRuntimeRange; HANDOFF_MAX_EFI_RUNTIME],
+}
+
+impl KernelHandoff {
+    /// Allocate a zeroed handoff block in LOADER_DATA pages
+    ///
+    /// The block is allocated with AllocatePages so it is physically
+    /// contiguous and identity mapped at the time of the jump.
+    pub fn allocate() -> Result<&'static mut KernelHandoff, uefi::Error> {
+        let size = core::mem::size_of::<KernelHandoff>();
+        let pages = (size + 0xFFF) / 0x1000;
+        let ptr = uefi::boot::allocate_pages(
+            AllocateType::AnyPages,
+            MemoryType::LOADER_DATA,
+            pages,
+        )?;
+
+        let handoff = ptr.as_ptr() as *mut KernelHandoff;
+        unsafe {
+            handoff.write(KernelHandoff {
+                magic: HANDOFF_MAGIC,
+                version: HANDOFF_VERSION,
+                size: size as u32,
+                acpi_rsdp: 0,
+                smbios_entry: 0,
+                system_table: 0,
+                kernel_base: 0,
+                kernel_size: 0,
+                framebuffer: FramebufferInfo::EMPTY,
+                memory_range_count: 0,
+                cmdline_len: 0,
+                memory_map: [MemoryRange::EMPTY; HANDOFF_MAX_MEMORY_RANGES],
+                cmdline: [0; HANDOFF_CMDLINE_MAX],
+                module_count: 0,
+                reserved0: 0,
+                modules: [BootModule::EMPTY; HANDOFF_MAX_MODULES],
+                devicetree: 0,
+                kernel_slide: 0,
+                rng_seed_len: 0,
+                boot_flags: 0,
+                rng_seed: [0; HANDOFF_RNG_SEED_MAX],
+                tpm_event_log: 0,
+                tpm_event_log_size: 0,
+                tpm_final_events: 0,
+                efi_runtime_count: 0,
+                reserved1: 0,
+                efi_runtime: [EfiRuntimeRange::EMPTY; HANDOFF_MAX_EFI_RUNTIME],
+            });
+            Ok(&mut *handoff)
+        }
+    }
+
+    /// Copy an ASCII command line into the handoff, truncating if needed
+    pub fn set_cmdline(&mut self, cmdline: &[u8]) {
+        let len = cmdline
+            .iter()
+            .take(HANDOFF_CMDLINE_MAX - 1)
+            .take_while(|&&c| c != 0)
+            .count();
+        self.cmdline[..len].copy_from_slice(&cmdline[..len]);
+        self.cmdline[len] = 0;
+        self.cmdline_len = len as u32;
+    }
+
+    /// Add a boot module, truncating its name if needed
+    ///
+    /// Returns false if the module table is full.
+    pub fn push_module(&mut self, base: u64, length: u64, name: &[u8]) -> bool {
+        let count = self.module_count as usize;
+        if count >= HANDOFF_MAX_MODULES {
+            return false;
+        }
+
+        let mut module = BootModule { base, length, name: [0; HANDOFF_MODULE_NAME_MAX] };
+        let n = name.len().min(HANDOFF_MODULE_NAME_MAX - 1);
+        module.name[..n].copy_from_slice(&name[..n]);
+
+        self.modules[count] = module;
+        self.module_count += 1;
+        true
+    }
+
+    /// Copy the boot RNG seed into the handoff, truncating if needed
+    pub fn set_rng_seed(&mut self, seed: &[u8]) {
+        let len = seed.len().min(HANDOFF_RNG_SEED_MAX);
+        self.rng_seed[..len].copy_from_slice(&seed[..len]);
+        self.rng_seed_len = len as u32;
+    }
+
+    /// Append a memory range, coalescing with the previous one if possible
+    ///
+    /// Must not allocate: this runs after ExitBootServices.
+    fn push_range(&mut self, range: MemoryRange) -> bool {
+        let count = self.memory_range_count as usize;
+        if count > 0 {
+            let prev = &mut self.memory_map[count - 1];
+            if prev.mem_type == range.mem_type && prev.base + prev.length == range.base {
+                prev.length += range.length;
+                return true;
+            }
+        }
+
+        if count >= HANDOFF_MAX_MEMORY_RANGES {
+            return false;
+        }
+
+        self.memory_map[count] = range;
+        self.memory_range_count += 1;
+        true
+    }
+
+    /// Record the runtime services regions of the final EFI memory map
+    ///
+    /// Must not allocate: this runs after ExitBootServices.
+    pub fn fill_efi_runtime(&mut self, map: &EfiMemoryMap) {
+        self.efi_runtime_count = 0;
+
+        for i in 0..map.entry_count() {
+            let desc = map.descriptor(i);
+            if desc.att.bits() & EFI_MEMORY_RUNTIME == 0 || desc.page_count == 0 {
+                continue;
+            }
+
+            let count = self.efi_runtime_count as usize;
+            if count >= HANDOFF_MAX_EFI_RUNTIME {
+                break;
+            }
+            self.efi_runtime[count] = EfiRuntimeRange {
+                phys_start: desc.phys_start,
+                page_count: desc.page_count,
+                attribute: desc.att.bits(),
+                efi_type: desc.ty.0,
+                reserved: 0,
+            };
+            self.efi_runtime_count += 1;
+        }
+    }
+
+    /// Fill the memory map from the final EFI memory map
+    ///
+    /// Entries are sorted by base address in place (no allocation) and
+    /// contiguous ranges of the same type are merged.
+    pub fn fill_memory_map(&mut self, map: &EfiMemoryMap) {
+        self.memory_range_count = 0;
+
+        for i in 0..map.entry_count() {
+            let desc = map.descriptor(i);
+
+            // Zircon pattern: Ignore zero-length entries
+            if desc.page_count == 0 {
+                continue;
+            }
+
+            let efi_memory_type: MemoryType = unsafe { core::mem::transmute(desc.ty) };
+            let range = MemoryRange {
+                base: desc.phys_start,
+                length: desc.page_count * 4096, // UEFI page size
+                mem_type: RustuxMemoryType::from(efi_memory_type),
+                reserved: 0,
+            };
+
+            // Firmware maps are normally sorted already, so the common case
+            // is an in-order append that coalesces immediately
+            let count = self.memory_range_count as usize;
+            if count == 0 || self.memory_map[count - 1].base <= range.base {
+                if !self.push_range(range) {
+                    break;
+                }
+                continue;
+            }
+
+            // Out of order: insert sorted by base address
+            if count >= HANDOFF_MAX_MEMORY_RANGES {
+                break;
+            }
+            let mut pos = count;
+            while pos > 0 && self.memory_map[pos - 1].base > range.base {
+                self.memory_map[pos] = self.memory_map[pos - 1];
+                pos -= 1;
+            }
+            self.memory_map[pos] = range;
+            self.memory_range_count += 1;
+        }
+
+        // Coalesce in a second pass now that the list is sorted
+        let count = self.memory_range_count as usize;
+        self.memory_range_count = 0;
+        for i in 0..count {
+            let range = self.memory_map[i];
+            self.push_range(range);
+        }
+    }
+}
+
+// ============================================================================
+// Memory Map Handling
+// ============================================================================
+
+/// Raw EFI memory map in a pre-allocated buffer
+///
+/// The buffer is allocated once, with slack, before the final
+/// GetMemoryMap call so that no allocation happens between obtaining the
+/// map key and calling ExitBootServices.
+pub struct EfiMemoryMap {
+    buffer: *mut u8,
+    capacity: usize,
+    size: usize,
+    entry_size: usize,
+    key: usize,
+}
+
+impl EfiMemoryMap {
+    /// Allocate a buffer large enough for the current memory map
+    pub fn allocate() -> Result<Self, uefi::Error> {
+        let mut map_size = 0usize;
+        let mut map_key = 0usize;
+        let mut entry_size = 0usize;
+        let mut entry_version = 0u32;
+
+        // Zircon pattern: First call to get required buffer size
+        let status = unsafe {
+            let st = uefi::table::system_table_raw().ok_or(Status::NOT_FOUND)?;
+            let boot_services = st.as_ref().boot_services;
+            ((*boot_services).get_memory_map)(
+                &mut map_size,
+                core::ptr::null_mut(),
+                &mut map_key,
+                &mut entry_size,
+                &mut entry_version,
+            )
+        };
+
+        if !status.is_success() && status != Status::BUFFER_TOO_SMALL {
+            return Err(uefi::Error::from(status));
+        }
+
+        // Zircon pattern: Add extra space for the allocation we are about
+        // to make and for anything firmware does before ExitBootServices
+        let capacity = map_size + entry_size * 16;
+        let pages = (capacity + 0xFFF) / 0x1000;
+        let buffer = uefi::boot::allocate_pages(
+            AllocateType::AnyPages,
+            MemoryType::LOADER_DATA,
+            pages,
+        )?;
+
+        Ok(Self {
+            buffer: buffer.as_ptr(),
+            capacity: pages * 0x1000,
+            size: 0,
+            entry_size,
+            key: 0,
+        })
+    }
+
+    /// Read the current memory map into the buffer
+    ///
+    /// Does not allocate, so the returned key stays valid until the next
+    /// boot-services allocation.
+    pub fn refresh(&mut self) -> Result<(), uefi::Error> {
+        let mut map_size = self.capacity;
+        let mut entry_version = 0u32;
+
+        let status = unsafe {
+            let st = uefi::table::system_table_raw().ok_or(Status::NOT_FOUND)?;
+            let boot_services = st.as_ref().boot_services;
+            ((*boot_services).get_memory_map)(
+                &mut map_size,
+                self.buffer as *mut uefi_raw::table::boot::MemoryDescriptor,
+                &mut self.key,
+                &mut self.entry_size,
+                &mut entry_version,
+            )
+        };
+
+        if !status.is_success() {
+            return Err(uefi::Error::from(status));
+        }
+
+        self.size = map_size;
+        Ok(())
+    }
+
+    /// Map key for ExitBootServices
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    /// Number of descriptors in the map
+    pub fn entry_count(&self) -> usize {
+        if self.entry_size == 0 {
+            0
+        } else {
+            self.size / self.entry_size
+        }
+    }
+
+    /// Get descriptor at index
+    ///
+    /// Descriptors must be indexed by `entry_size`, not by
+    /// `size_of::<MemoryDescriptor>()`, as firmware may use a larger stride.
+    pub fn descriptor(&self, index: usize) -> &uefi_raw::table::boot::MemoryDescriptor {
+        unsafe {
+            &*(self.buffer.add(index * self.entry_size)
+                as *const uefi_raw::table::boot::MemoryDescriptor)
+        }
+    }
+}
+
+// ============================================================================
+// ExitBootServices
+// ============================================================================
+
+/// Exit boot services with the correct map key
+///
+/// Per the UEFI spec, ExitBootServices may fail with INVALID_PARAMETER if
+/// the memory map changed after the key was obtained (e.g. a timer event
+/// allocated memory). In that case the map is re-read and the call is
+/// retried. No console output or allocation is allowed in between.
+///
+/// On success, the final memory map is left in `map`.
+pub fn exit_boot_services(map: &mut EfiMemoryMap) -> Result<(), uefi::Error> {
+    const MAX_ATTEMPTS: usize = 4;
+
+    let image = uefi::boot::image_handle();
+
+    for _ in 0..MAX_ATTEMPTS {
+        map.refresh()?;
+
+        let status = unsafe {
+            let st = uefi::table::system_table_raw().ok_or(Status::NOT_FOUND)?;
+            let boot_services = st.as_ref().boot_services;
+            ((*boot_services).exit_boot_services)(image.as_ptr(), map.key())
+        };
+
+        if status.is_success() {
+            return Ok(());
+        }
+
+        if status != Status::INVALID_PARAMETER {
+            return Err(uefi::Error::from(status));
+        }
+    }
+
+    Err(uefi::Error::from(Status::ABORTED))
+}
+
+/// Size of the stack the kernel is entered on
+pub const BOOT_STACK_SIZE: usize = 64 * 1024;
+
+/// Allocate the boot stack, returning its top
+///
+/// The stack is LOADER_DATA so it is identity mapped by the boot page
+/// tables; the kernel moves off it once it has its own.
+pub fn allocate_boot_stack() -> Result<u64, uefi::Error> {
+    let pages = BOOT_STACK_SIZE / 0x1000;
+    let base = uefi::boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages)?;
+    Ok(base.as_ptr() as u64 + BOOT_STACK_SIZE as u64)
+}
+
+/// Exit boot services and jump to the kernel
+///
+/// The kernel is entered at `entry` with the SysV calling convention, the
+/// physical address of the handoff block as its only argument, interrupts
+/// disabled, CR3 set to `page_tables` and the stack at `stack_top`.
+///
+/// Only returns if ExitBootServices failed, in which case boot services
+/// are still available and the error can be reported.
+///
+/// # Safety
+///
+/// `entry` must be the virtual entry of a loaded, relocated kernel image
+/// mapped by `page_tables`, which must also identity map this loader, the
+/// handoff block and the stack. After this call no UEFI boot service may
+/// be used.
+pub unsafe fn boot_kernel(
+    entry: u64,
+    page_tables: u64,
+    stack_top: u64,
+    handoff: &'static mut KernelHandoff,
+    map: &mut EfiMemoryMap,
+) -> uefi::Error {
+    if let Err(e) = exit_boot_services(map) {
+        return e;
+    }
+
+    // Boot services are gone - from here on only touch memory we own
+    handoff.fill_memory_map(map);
+    handoff.fill_efi_runtime(map);
+
+    // Disable interrupts (the kernel installs its own IDT), switch to the
+    // kernel's address space and stack, and call with a 16-byte aligned
+    // stack so the entry sees a normal frame
+    core::arch::asm!(
+        "cli",
+        "mov cr3, {tables}",
+        "mov rsp, {stack}",
+        "xor ebp, ebp",
+        "call {entry}",
+        "2:",
+        "hlt",
+        "jmp 2b",
+        tables = in(reg) page_tables,
+        stack = in(reg) stack_top & !0xF,
+        entry = in(reg) entry,
+        in("rdi") handoff as *const KernelHandoff,
+        options(noreturn),
+    )
+}
@@ -0,0 +1,165 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel load address randomization
+//!
+//! A relocatable kernel runs moved by the distance it was moved in
+//! physical memory, so picking a random physical load address randomizes
+//! its base. Randomness comes from the
+//! firmware's EFI_RNG_PROTOCOL when present, otherwise from RDRAND and
+//! the TSC. More bytes from the same source seed the kernel CPRNG
+//! through the handoff.
+//!
+//! Randomization is skipped with `kernel.aslr=false` on the command line
+//! (the switch the kernel itself reads), or if the image carries no
+//! relocations.
+
+use uefi::boot::{AllocateType, MemoryType};
+use uefi::proto::rng::Rng;
+
+/// Load addresses are multiples of this (one large page)
+pub const KASLR_ALIGN: u64 = 2 * 1024 * 1024;
+
+/// Lowest randomized load address
+const KASLR_MIN: u64 = 16 * 1024 * 1024;
+
+/// Randomized images end below this address
+const KASLR_MAX: u64 = 1024 * 1024 * 1024;
+
+/// Random slots tried before falling back to the preferred base
+const KASLR_ATTEMPTS: usize = 32;
+
+/// Check whether the ASCII command line disables randomization
+pub fn enabled(cmdline: &[u8]) -> bool {
+    let mut word = [0u8; 32];
+    let mut len = 0;
+    let mut enabled = true;
+    let chars = cmdline.iter().copied().chain(core::iter::once(b' '));
+
+    for c in chars {
+        if c != b' ' {
+            if len < word.len() {
+                word[len] = c;
+            }
+            len += 1;
+            continue;
+        }
+
+        match &word[..len.min(word.len())] {
+            b"kernel.aslr=false" | b"kernel.aslr=0" | b"kernel.aslr=off" => {
+                enabled = false
+            }
+            b"kernel.aslr" | b"kernel.aslr=true" | b"kernel.aslr=1" | b"kernel.aslr=on" => {
+                enabled = true
+            }
+            _ => {}
+        }
+        len = 0;
+    }
+
+    enabled
+}
+
+/// Fill `buf` with boot randomness
+///
+/// Returns true if the bytes came from the firmware RNG or RDRAND, false
+/// if only the TSC was available.
+pub fn fill_random(buf: &mut [u8]) -> bool {
+    if firmware_rng(buf) {
+        return true;
+    }
+
+    let rdrand = has_rdrand();
+    for chunk in buf.chunks_mut(8) {
+        let mut value = tsc_mix();
+        if rdrand {
+            value ^= rdrand64().unwrap_or(0);
+        }
+        chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+    }
+    rdrand
+}
+
+/// Read from EFI_RNG_PROTOCOL, if the firmware has it
+fn firmware_rng(buf: &mut [u8]) -> bool {
+    let handle = match uefi::boot::get_handle_for_protocol::<Rng>() {
+        Ok(handle) => handle,
+        Err(_) => return false,
+    };
+    let mut rng = match uefi::boot::open_protocol_exclusive::<Rng>(handle) {
+        Ok(rng) => rng,
+        Err(_) => return false,
+    };
+    rng.get_rng(None, buf).is_ok()
+}
+
+/// Check CPUID.01H:ECX.RDRAND[bit 30]
+fn has_rdrand() -> bool {
+    let ecx = unsafe { core::arch::x86_64::__cpuid(1).ecx };
+    ecx & (1 << 30) != 0
+}
+
+/// Read RDRAND, retrying a few times as Intel recommends
+fn rdrand64() -> Option<u64> {
+    for _ in 0..10 {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!("rdrand {0}", "setc {1}", out(reg) value, out(reg_byte) ok, options(nomem, nostack));
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Fold TSC jitter around a short busy loop into a word
+fn tsc_mix() -> u64 {
+    let mut value = 0u64;
+    for _ in 0..64 {
+        let start = unsafe { core::arch::x86_64::_rdtsc() };
+        for _ in 0..(value & 0x1f) + 1 {
+            core::hint::spin_loop();
+        }
+        let delta = unsafe { core::arch::x86_64::_rdtsc() }.wrapping_sub(start);
+        value = value.rotate_left(5) ^ delta ^ start;
+    }
+    value
+}
+
+/// Allocate `pages` at a random aligned address in the KASLR window
+///
+/// Returns None if no random slot could be allocated, in which case the
+/// caller uses its normal placement.
+pub fn allocate_random(pages: usize, seed: u64) -> Option<*mut u8> {
+    let size = (pages as u64) * 0x1000;
+    if size + KASLR_MIN > KASLR_MAX {
+        return None;
+    }
+    let slots = (KASLR_MAX - KASLR_MIN - size) / KASLR_ALIGN + 1;
+
+    let mut state = seed;
+    for _ in 0..KASLR_ATTEMPTS {
+        // splitmix64 step
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        let addr = KASLR_MIN + (z % slots) * KASLR_ALIGN;
+        if let Ok(ptr) = uefi::boot::allocate_pages(
+            AllocateType::Address(addr),
+            MemoryType::LOADER_CODE,
+            pages,
+        ) {
+            return Some(ptr.as_ptr());
+        }
+    }
+
+    None
+}
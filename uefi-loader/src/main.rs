@@ -1,23 +1,45 @@
-#![no_std]
-#![no_main]
+// Unit tests run on the host, against std
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
 extern crate alloc;
 
 use uefi::prelude::*;
 use uefi::proto::media::fs::SimpleFileSystem;
-use uefi::proto::media::file::{File, FileAttribute, FileMode};
+use uefi::proto::media::file::{Directory, File, FileAttribute, FileInfo, FileMode, FileType};
 use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::console::gop::{GraphicsOutput, PixelFormat};
+use uefi::boot::{OpenProtocolAttributes, OpenProtocolParams};
 use uefi::table::cfg;
 use uefi::table::system_table_raw;
 use uefi::boot::{AllocateType, MemoryType};
 use uefi::Status;
 use alloc::vec::Vec;
+use core::fmt::Write;
+
+mod config;
+mod ed25519;
+mod elf;
+mod handoff;
+mod kaslr;
+mod menu;
+mod paging;
+mod secureboot;
+mod sha512;
+mod tpm;
+
+use elf::LoadedKernel;
+use handoff::{
+    EfiMemoryMap, FramebufferFormat, FramebufferInfo, KernelHandoff, MemoryRange, RustuxMemoryType,
+};
 
 // Global allocator for UEFI
+#[cfg(not(test))]
 #[global_allocator]
 static ALLOCATOR: uefi::allocator::Allocator = uefi::allocator::Allocator;
 
 // Required for UEFI no_std
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     loop {
@@ -25,78 +47,6 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
     }
 }
 
-// ============================================================================
-// Memory Types - Adapted from Zircon's efi_memory_type conversion
-// ============================================================================
-
-/// Rustux memory type for kernel handoff
-#[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum RustuxMemoryType {
-    Available = 1,
-    Reserved = 2,
-    Reclaimable = 3,
-    Peripheral = 4,
-}
-
-impl From<MemoryType> for RustuxMemoryType {
-    fn from(efi_type: MemoryType) -> Self {
-        match efi_type {
-            MemoryType::LOADER_CODE
-            | MemoryType::LOADER_DATA
-            | MemoryType::BOOT_SERVICES_CODE
-            | MemoryType::BOOT_SERVICES_DATA
-            | MemoryType::CONVENTIONAL => RustuxMemoryType::Available,
-
-            MemoryType::MMIO
-            | MemoryType::MMIO_PORT_SPACE => RustuxMemoryType::Peripheral,
-
-            MemoryType::ACPI_RECLAIM
-            | MemoryType::ACPI_NON_VOLATILE => RustuxMemoryType::Reclaimable,
-
-            _ => RustuxMemoryType::Reserved,
-        }
-    }
-}
-
-/// Memory range descriptor for kernel handoff
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct MemoryRange {
-    pub base: u64,
-    pub length: u64,
-    pub mem_type: RustuxMemoryType,
-}
-
-/// Kernel handoff structure - passed to kernel on boot
-#[repr(C)]
-#[derive(Debug)]
-pub struct KernelHandoff {
-    pub memory_map: Vec<MemoryRange>,
-    pub acpi_rsdp: Option<u64>,
-    pub smbios_entry: Option<u64>,
-    pub system_table: u64,
-    pub framebuffer: Option<FramebufferInfo>,
-}
-
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct FramebufferInfo {
-    pub base: u64,
-    pub size: u64,
-    pub width: u32,
-    pub height: u32,
-    pub stride: u32,
-    pub format: FramebufferFormat,
-}
-
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub enum FramebufferFormat {
-    RGB,
-    BGR,
-}
-
 // ============================================================================
 // UEFI Bootloader - Incorporating Zircon patterns
 // ============================================================================
@@ -136,6 +86,15 @@ fn main() -> Status {
             }
         }
 
+        match find_framebuffer() {
+            Some(_fb) => {
+                let _ = stdout.output_string(cstr16!("  - GOP framebuffer: Found\r\n"));
+            }
+            None => {
+                let _ = stdout.output_string(cstr16!("  - GOP framebuffer: Not present (serial only)\r\n"));
+            }
+        }
+
         let _ = stdout.output_string(cstr16!("\r\n\
 [Phase 3] Memory Map Acquisition\r\n\
 "));
@@ -167,7 +126,7 @@ fn main() -> Status {
 
         let _ = stdout.output_string(cstr16!("\r\n\
 [Phase 4] Kernel Loading\r\n\
-  - Searching for /EFI/Rustux/kernel.efi\r\n\
+  - Reading /EFI/Rustux/boot.cfg\r\n\
 "));
 
         match load_and_start_kernel() {
@@ -197,96 +156,6 @@ fn main() -> Status {
 // Memory Map Handling - Zircon-inspired implementation
 // ============================================================================
 
-/// Zircon-style GetMemoryMap implementation
-/// Returns the memory map buffer, entry size, and map key for ExitBootServices
-struct EfiMemoryMapInfo {
-    buffer: *mut u8,
-    size: usize,
-    entry_size: usize,
-    key: usize,
-}
-
-/// Get EFI memory map - Zircon pattern
-/// First call gets size, second call fills the buffer
-fn get_efi_memory_map_raw() -> Result<EfiMemoryMapInfo, uefi::Error> {
-    // Zircon pattern: First call to get required buffer size
-    let mut map_size = 0usize;
-    let mut map_key = 0usize;
-    let mut entry_size = 0usize;
-    let mut entry_version = 0u32;
-
-    let status = unsafe {
-        let bt = uefi::table::system_table_raw()
-            .ok_or(uefi::Status::NOT_FOUND)?;
-        let st = bt.as_ref();
-        let boot_services = st.boot_services;
-
-        // GetMemoryMap first call - get buffer size
-        // EFI_STATUS GetMemoryMap(
-        //   IN OUT UINTN  *MemoryMapSize,
-        //   OUT VOID *MemoryMap,
-        //   OUT UINTN *MapKey,
-        //   OUT UINTN *DescriptorSize,
-        //   OUT UINT32 *DescriptorVersion
-        // );
-        let get_memory_map = (*boot_services).get_memory_map;
-        get_memory_map(
-            &mut map_size,
-            core::ptr::null_mut(),
-            &mut map_key,
-            &mut entry_size,
-            &mut entry_version,
-        )
-    };
-
-    // EFI_BUFFER_TOO_SMALL (5) is expected on first call
-    if !status.is_success() && status != Status::BUFFER_TOO_SMALL {
-        return Err(uefi::Error::from(status));
-    }
-
-    // Zircon pattern: Add extra space for dynamic allocations
-    // during ExitBootServices
-    map_size += entry_size * 8;
-
-    // Allocate buffer for memory map
-    let buffer_pages = (map_size + 0xFFF) / 0x1000;
-    let buffer = uefi::boot::allocate_pages(
-        AllocateType::AnyPages,
-        MemoryType::LOADER_DATA,
-        buffer_pages,
-    )?;
-
-    let buffer_ptr = buffer.as_ptr() as *mut u8;
-
-    // Get actual memory map
-    let status = unsafe {
-        let bt = uefi::table::system_table_raw()
-            .ok_or(uefi::Status::NOT_FOUND)?;
-        let st = bt.as_ref();
-        let boot_services = st.boot_services;
-
-        let get_memory_map = (*boot_services).get_memory_map;
-        get_memory_map(
-            &mut map_size,
-            buffer_ptr as *mut uefi_raw::table::boot::MemoryDescriptor,
-            &mut map_key,
-            &mut entry_size,
-            &mut entry_version,
-        )
-    };
-
-    if !status.is_success() {
-        return Err(uefi::Error::from(status));
-    }
-
-    Ok(EfiMemoryMapInfo {
-        buffer: buffer_ptr,
-        size: map_size,
-        entry_size,
-        key: map_key,
-    })
-}
-
 /// Zircon-style memory range coalescing
 /// Combine contiguous ranges of the same type
 fn coalesce_ranges(ranges: &mut Vec<MemoryRange>) {
@@ -320,18 +189,14 @@ fn coalesce_ranges(ranges: &mut Vec<MemoryRange>) {
 
 /// Convert EFI memory map to Rustux format - Zircon pattern
 fn get_efi_memory_map(_stdout: &mut uefi::proto::console::text::Output) -> Result<Vec<MemoryRange>, uefi::Error> {
-    let map_info = get_efi_memory_map_raw()?;
+    let mut map = EfiMemoryMap::allocate()?;
+    map.refresh()?;
 
     // Convert EFI memory descriptors to Rustux format
-    let num_entries = map_info.size / map_info.entry_size;
     let mut ranges = Vec::new();
 
-    for i in 0..num_entries {
-        let desc_ptr = unsafe {
-            (map_info.buffer as *const u8).add(i * map_info.entry_size)
-                as *const uefi_raw::table::boot::MemoryDescriptor
-        };
-        let desc = unsafe { &*desc_ptr };
+    for i in 0..map.entry_count() {
+        let desc = map.descriptor(i);
 
         // Zircon pattern: Ignore zero-length entries
         if desc.page_count > 0 {
@@ -341,6 +206,7 @@ fn get_efi_memory_map(_stdout: &mut uefi::proto::console::text::Output) -> Resul
                 base: desc.phys_start,
                 length: desc.page_count * 4096, // UEFI page size
                 mem_type: RustuxMemoryType::from(efi_memory_type),
+                reserved: 0,
             };
             ranges.push(range);
         }
@@ -359,284 +225,183 @@ fn get_efi_memory_map(_stdout: &mut uefi::proto::console::text::Output) -> Resul
 /// ACPI RSDP (Root System Description Pointer) signature
 const ACPI_RSDP_SIGNATURE: u64 = 0x2052545020445352; // "RSD PTR "
 
-/// Find ACPI RSDP from UEFI configuration tables - Zircon pattern
-fn find_acpi_rsdp() -> Option<u64> {
-    // Zircon pattern: Search configuration tables for ACPI GUIDs
-    // Try ACPI 2.0 GUID first, then ACPI 1.0 GUID
-    let acpi2_guid = cfg::ConfigTableEntry::ACPI2_GUID;
-
-    if let Some(st) = system_table_raw() {
-        let system_table: &uefi_raw::table::system::SystemTable = unsafe { st.as_ref() };
+/// Look up a vendor table in the UEFI configuration table by GUID
+fn find_config_table(guid: uefi::Guid) -> Option<u64> {
+    let st = system_table_raw()?;
+    let system_table: &uefi_raw::table::system::SystemTable = unsafe { st.as_ref() };
 
-        for i in 0..system_table.number_of_configuration_table_entries {
-            let entry_ptr = unsafe {
-                system_table.configuration_table.add(i)
-            };
-            let entry = unsafe { &*entry_ptr };
+    for i in 0..system_table.number_of_configuration_table_entries {
+        let entry_ptr = unsafe {
+            system_table.configuration_table.add(i)
+        };
+        let entry = unsafe { &*entry_ptr };
 
-            if entry.vendor_guid == acpi2_guid && !entry.vendor_table.is_null() {
-                // Found ACPI table
-                let rsdp_ptr = entry.vendor_table as u64;
-                return Some(rsdp_ptr);
-            }
+        if entry.vendor_guid == guid && !entry.vendor_table.is_null() {
+            return Some(entry.vendor_table as u64);
         }
     }
 
     None
 }
 
-// ============================================================================
-// PE/COFF Format Definitions
-// ============================================================================
-
-/// PE/COFF header structures for manual image loading
-#[repr(C)]
-struct DosHeader {
-    e_magic: u16,           // Magic number (0x5A4D = "MZ")
-    e_cblp: u16,
-    e_cp: u16,
-    e_crlc: u16,
-    e_cparhdr: u16,
-    e_minalloc: u16,
-    e_maxalloc: u16,
-    e_ss: u16,
-    e_sp: u16,
-    e_csum: u16,
-    e_ip: u16,
-    e_cs: u16,
-    e_lfarlc: u16,
-    e_ovno: u16,
-    e_res: [u16; 4],
-    e_oemid: u16,
-    e_oeminfo: u16,
-    e_res2: [u16; 10],
-    e_lfanew: u32,          // Offset to PE header
-}
-
-/// COFF File Header
-#[repr(C)]
-struct CoffFileHeader {
-    machine: u16,
-    number_of_sections: u16,
-    time_date_stamp: u32,
-    pointer_to_symbol_table: u32,
-    number_of_symbols: u32,
-    size_of_optional_header: u16,
-    characteristics: u16,
+/// Find ACPI RSDP from UEFI configuration tables - Zircon pattern
+fn find_acpi_rsdp() -> Option<u64> {
+    // Zircon pattern: Search configuration tables for ACPI GUIDs
+    // Try ACPI 2.0 GUID first, then ACPI 1.0 GUID
+    find_config_table(cfg::ConfigTableEntry::ACPI2_GUID)
+        .or_else(|| find_config_table(cfg::ConfigTableEntry::ACPI_GUID))
 }
 
-/// PE Optional Header (PE32+ format)
-#[repr(C)]
-struct PeOptionalHeader {
-    magic: u16,                     // 0x20b for PE32+
-    major_linker_version: u8,
-    minor_linker_version: u8,
-    size_of_code: u32,
-    size_of_initialized_data: u32,
-    size_of_uninitialized_data: u32,
-    address_of_entry_point: u32,
-    base_of_code: u32,
-    image_base: u64,
-    section_alignment: u32,
-    file_alignment: u32,
-    major_os_version: u16,
-    minor_os_version: u16,
-    major_image_version: u16,
-    minor_image_version: u16,
-    major_subsystem_version: u16,
-    minor_subsystem_version: u16,
-    win32_version_value: u32,
-    size_of_image: u32,
-    size_of_headers: u32,
-    check_sum: u32,
-    subsystem: u16,
-    dll_characteristics: u16,
-    size_of_stack_reserve: u64,
-    size_of_stack_commit: u64,
-    size_of_heap_reserve: u64,
-    size_of_heap_commit: u64,
-    loader_flags: u32,
-    number_of_rva_and_sizes: u32,
-}
+/// EFI_DTB_TABLE_GUID - flattened device tree installed by firmware (arm64/riscv64)
+const DTB_TABLE_GUID: uefi::Guid = uefi::guid!("b1b621d5-f19c-41a5-830b-d9152c69aae0");
 
-/// Data Directory entry
-#[repr(C)]
-struct DataDirectory {
-    virtual_address: u32,
-    size: u32,
+/// Find the device tree blob, if the firmware provides one
+fn find_devicetree() -> Option<u64> {
+    find_config_table(DTB_TABLE_GUID)
 }
 
-/// Section header
-#[repr(C)]
-struct SectionHeader {
-    name: [u8; 8],
-    virtual_size: u32,
-    virtual_address: u32,
-    size_of_raw_data: u32,
-    pointer_to_raw_data: u32,
-    pointer_to_relocations: u32,
-    pointer_to_line_numbers: u32,
-    number_of_relocations: u16,
-    number_of_line_numbers: u16,
-    characteristics: u32,
+/// Find the SMBIOS entry point - prefer the 64-bit SMBIOS 3.0 table
+fn find_smbios_entry() -> Option<u64> {
+    find_config_table(cfg::ConfigTableEntry::SMBIOS3_GUID)
+        .or_else(|| find_config_table(cfg::ConfigTableEntry::SMBIOS_GUID))
 }
 
 // ============================================================================
-// Kernel Loading
+// Framebuffer Discovery - Graphics Output Protocol
 // ============================================================================
 
-/// Validate and display PE/COFF header information
-fn validate_pe_coff(kernel_data: *const u8, file_size: usize) -> Result<(), &'static str> {
-    uefi::system::with_stdout(|stdout| {
-        let _ = stdout.output_string(cstr16!("  - Validating PE/COFF header...\r\n"));
-    });
-
-    // Check minimum size
-    if file_size < 64 {
-        return Err("File too small for DOS header");
-    }
-
-    // Parse DOS header
-    let dos_header = unsafe { &*(kernel_data as *const DosHeader) };
+/// Query the Graphics Output Protocol for the current mode
+///
+/// The protocol is opened with GET_PROTOCOL rather than exclusively so
+/// the firmware text console bound to the same GOP keeps working until
+/// ExitBootServices. Only linear 32bpp RGB/BGR modes are reported; BLT-only
+/// and bitmask modes cannot be driven without boot services.
+fn find_framebuffer() -> Option<FramebufferInfo> {
+    let handle = uefi::boot::get_handle_for_protocol::<GraphicsOutput>().ok()?;
+
+    let mut gop = unsafe {
+        uefi::boot::open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle,
+                agent: uefi::boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+        .ok()?
+    };
 
-    uefi::system::with_stdout(|stdout| {
-        let _ = stdout.output_string(cstr16!("    - DOS signature: "));
-        if dos_header.e_magic == 0x5A4D {
-            let _ = stdout.output_string(cstr16!("MZ (OK)\r\n"));
-        } else {
-            let _ = stdout.output_string(cstr16!("Invalid! (expected MZ)\r\n"));
-        }
-        let _ = stdout.output_string(cstr16!("    - PE offset: "));
-        // Simple hex display for PE offset
-        let pe_off = dos_header.e_lfanew;
-        let hex = [cstr16!("0"), cstr16!("1"), cstr16!("2"), cstr16!("3"),
-                   cstr16!("4"), cstr16!("5"), cstr16!("6"), cstr16!("7"),
-                   cstr16!("8"), cstr16!("9"), cstr16!("A"), cstr16!("B"),
-                   cstr16!("C"), cstr16!("D"), cstr16!("E"), cstr16!("F")];
-        let _ = stdout.output_string(cstr16!("0x"));
-        let _ = stdout.output_string(hex[((pe_off >> 28) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((pe_off >> 24) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((pe_off >> 20) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((pe_off >> 16) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((pe_off >> 12) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((pe_off >> 8) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((pe_off >> 4) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[(pe_off & 0xF) as usize]);
-        let _ = stdout.output_string(cstr16!("\r\n"));
-    });
+    let mode = gop.current_mode_info();
+    let format = match mode.pixel_format() {
+        PixelFormat::Rgb => FramebufferFormat::RGB,
+        PixelFormat::Bgr => FramebufferFormat::BGR,
+        _ => return None,
+    };
 
-    // Check PE offset is valid
-    let pe_offset = dos_header.e_lfanew as usize;
-    if pe_offset == 0 || pe_offset + 4 > file_size {
-        return Err("Invalid PE offset");
-    }
+    let (width, height) = mode.resolution();
+    let mut fb = gop.frame_buffer();
 
-    // Check PE signature
-    let pe_signature = unsafe { *(kernel_data.add(pe_offset) as *const u32) };
-    if pe_signature != 0x00004550 {
-        uefi::system::with_stdout(|stdout| {
-            let _ = stdout.output_string(cstr16!("    - PE signature: Invalid! (expected PE00)\r\n"));
-        });
-        return Err("Invalid PE signature");
-    }
-
-    uefi::system::with_stdout(|stdout| {
-        let _ = stdout.output_string(cstr16!("    - PE signature: PE00 (OK)\r\n"));
-    });
+    Some(FramebufferInfo {
+        base: fb.as_mut_ptr() as u64,
+        size: fb.size() as u64,
+        width: width as u32,
+        height: height as u32,
+        stride: mode.stride() as u32,
+        format,
+    })
+}
 
-    // Parse COFF file header
-    let coff_offset = pe_offset + 4;
-    if coff_offset + core::mem::size_of::<CoffFileHeader>() > file_size {
-        return Err("File too small for COFF header");
-    }
+// ============================================================================
+// Kernel Loading
+// ============================================================================
 
-    let coff_header = unsafe {
-        &*(kernel_data.add(coff_offset) as *const CoffFileHeader)
+/// Read a file from the loader's volume into its own LOADER_DATA pages
+fn load_file(root: &mut Directory, path: &str) -> uefi::Result<&'static mut [u8]> {
+    let path = config::path_ucs2(path).ok_or(Status::INVALID_PARAMETER)?;
+    let handle = root.open(&path, FileMode::Read, FileAttribute::empty())?;
+    let mut file = match handle.into_type()? {
+        FileType::Regular(file) => file,
+        FileType::Dir(_) => return Err(Status::NOT_FOUND.into()),
     };
 
-    uefi::system::with_stdout(|stdout| {
-        let _ = stdout.output_string(cstr16!("    - Machine type: "));
-        // 0x8664 = AMD64/x86-64
-        if coff_header.machine == 0x8664 {
-            let _ = stdout.output_string(cstr16!("0x8664 (x86-64, OK)\r\n"));
-        } else {
-            let _ = stdout.output_string(cstr16!("Unknown (Not x86-64!)\r\n"));
-        }
-        let _ = stdout.output_string(cstr16!("    - Characteristics: "));
-        if coff_header.characteristics & 0x0020 != 0 {
-            let _ = stdout.output_string(cstr16!("EXECUTABLE_IMAGE\r\n"));
-        } else {
-            let _ = stdout.output_string(cstr16!("Unknown\r\n"));
-        }
-    });
-
-    // Parse optional header
-    let opt_header_offset = coff_offset + core::mem::size_of::<CoffFileHeader>();
-    if opt_header_offset + core::mem::size_of::<PeOptionalHeader>() > file_size {
-        return Err("File too small for optional header");
-    }
-
-    let opt_header = unsafe {
-        &*(kernel_data.add(opt_header_offset) as *const PeOptionalHeader)
-    };
+    let mut info_buf = [0u8; 256];
+    let info = file.get_info::<FileInfo>(&mut info_buf)
+        .map_err(|e| uefi::Error::from(e.status()))?;
+    let file_size = info.file_size() as usize;
 
-    uefi::system::with_stdout(|stdout| {
-        let _ = stdout.output_string(cstr16!("    - PE Magic: "));
-        // 0x20b = PE32+ (64-bit)
-        if opt_header.magic == 0x020b {
-            let _ = stdout.output_string(cstr16!("0x20b (PE32+, OK)\r\n"));
-        } else {
-            let _ = stdout.output_string(cstr16!("Unknown (Not PE32+!)\r\n"));
-        }
-        let _ = stdout.output_string(cstr16!("    - Subsystem: "));
-        // 0x0a = EFI application
-        if opt_header.subsystem == 0x0a {
-            let _ = stdout.output_string(cstr16!("0x0a (EFI_APP, OK)\r\n"));
-        } else if opt_header.subsystem == 0x0b {
-            let _ = stdout.output_string(cstr16!("0x0b (EFI_BOOT_SERVICE_DRIVER)\r\n"));
-        } else if opt_header.subsystem == 0x0c {
-            let _ = stdout.output_string(cstr16!("0x0c (EFI_RUNTIME_DRIVER)\r\n"));
-        } else {
-            let _ = stdout.output_string(cstr16!("Unknown (Not EFI!)\r\n"));
-        }
-        let _ = stdout.output_string(cstr16!("    - Entry point RVA: "));
-        let ep = opt_header.address_of_entry_point;
-        let hex = [cstr16!("0"), cstr16!("1"), cstr16!("2"), cstr16!("3"),
-                   cstr16!("4"), cstr16!("5"), cstr16!("6"), cstr16!("7"),
-                   cstr16!("8"), cstr16!("9"), cstr16!("A"), cstr16!("B"),
-                   cstr16!("C"), cstr16!("D"), cstr16!("E"), cstr16!("F")];
-        let _ = stdout.output_string(cstr16!("0x"));
-        let _ = stdout.output_string(hex[((ep >> 28) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((ep >> 24) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((ep >> 20) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((ep >> 16) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((ep >> 12) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((ep >> 8) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[((ep >> 4) & 0xF) as usize]);
-        let _ = stdout.output_string(hex[(ep & 0xF) as usize]);
-        let _ = stdout.output_string(cstr16!("\r\n"));
-    });
+    let num_pages = (file_size + 0xFFF) / 0x1000;
+    let data = uefi::boot::allocate_pages(
+        AllocateType::AnyPages,
+        MemoryType::LOADER_DATA,
+        num_pages.max(1),
+    )?;
+    let data = unsafe { core::slice::from_raw_parts_mut(data.as_ptr(), file_size) };
+    let read = file.read(data).map_err(|e| uefi::Error::from(e.status()))?;
+    Ok(&mut data[..read])
+}
 
-    // Validate machine type
-    if coff_header.machine != 0x8664 {
-        return Err("Wrong machine type (not x86-64)");
+/// Build the handoff block, exit boot services and enter the kernel
+///
+/// Only returns on failure, while boot services are still available.
+fn handoff_to_kernel(
+    kernel: &LoadedKernel,
+    initrd: Option<(&[u8], &str)>,
+    cmdline: &str,
+    rng_seed: Option<&[u8]>,
+    boot_flags: u32,
+    tpm_log: Option<tpm::EventLog>,
+) -> uefi::Result {
+    let handoff = KernelHandoff::allocate()?;
+
+    handoff.acpi_rsdp = find_acpi_rsdp().unwrap_or(0);
+    handoff.smbios_entry = find_smbios_entry().unwrap_or(0);
+    handoff.devicetree = find_devicetree().unwrap_or(0);
+    handoff.system_table = system_table_raw()
+        .map(|st| st.as_ptr() as u64)
+        .unwrap_or(0);
+    handoff.kernel_base = kernel.base;
+    handoff.kernel_size = kernel.size;
+    handoff.kernel_slide = kernel.slide;
+    handoff.boot_flags = boot_flags;
+    if let Some(log) = tpm_log {
+        handoff.tpm_event_log = log.base;
+        handoff.tpm_event_log_size = log.size;
+        handoff.tpm_final_events = find_config_table(tpm::FINAL_EVENTS_TABLE_GUID).unwrap_or(0);
     }
-
-    // Validate PE32+ magic
-    if opt_header.magic != 0x020b {
-        return Err("Wrong PE magic (not PE32+)");
+    if let Some(seed) = rng_seed {
+        handoff.set_rng_seed(seed);
     }
-
-    // Validate subsystem
-    if opt_header.subsystem != 0x0a && opt_header.subsystem != 0x0b && opt_header.subsystem != 0x0c {
-        return Err("Wrong subsystem (not EFI)");
+    let mut framebuffer_end = 0;
+    if let Some(fb) = find_framebuffer() {
+        framebuffer_end = fb.base + fb.size;
+        handoff.framebuffer = fb;
+    }
+    handoff.set_cmdline(cmdline.as_bytes());
+    if let Some((data, path)) = initrd {
+        let name = path.rsplit('\\').next().unwrap_or(path);
+        handoff.push_module(data.as_ptr() as u64, data.len() as u64, name.as_bytes());
     }
 
+    // The kernel is entered on its own page tables and stack
+    let tables = paging::build(kernel, framebuffer_end).map_err(|_| {
+        uefi::system::with_stdout(|stdout| {
+            let _ = stdout.output_string(cstr16!("  - Page table setup failed\r\n"));
+        });
+        uefi::Error::from(Status::LOAD_ERROR)
+    })?;
+    let stack_top = handoff::allocate_boot_stack()?;
+
+    // Allocate the memory map buffer last; nothing may allocate after this
+    let mut map = EfiMemoryMap::allocate()?;
+
     uefi::system::with_stdout(|stdout| {
-        let _ = stdout.output_string(cstr16!("  - PE/COFF validation: PASSED\r\n"));
+        let _ = stdout.output_string(cstr16!("  - Exiting boot services...\r\n"));
     });
 
-    Ok(())
+    let err = unsafe {
+        handoff::boot_kernel(kernel.entry, tables.root(), stack_top, handoff, &mut map)
+    };
+    Err(err)
 }
 
 /// Reboot the system using UEFI runtime services
@@ -693,8 +458,39 @@ fn show_error_menu(_error_message: &str) -> ! {
     reboot_system();
 }
 
-/// Load and start the kernel.efi from disk
+/// Refuse to boot an image that failed verification
+///
+/// Unlike `show_error_menu`, the reason stays on screen until a key is
+/// pressed, so it can be read before the system reboots.
+fn show_verification_error(path: &str, reason: &str) -> ! {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.output_string(cstr16!("\r\n"));
+        let _ = stdout.output_string(cstr16!("==================================================================\r\n"));
+        let _ = stdout.output_string(cstr16!("||                  SECURE BOOT VIOLATION                      ||\r\n"));
+        let _ = stdout.output_string(cstr16!("==================================================================\r\n\r\n"));
+        let _ = write!(stdout, "  Image:  {}\r\n  Reason: {}\r\n\r\n", path, reason);
+        let _ = stdout.output_string(cstr16!("The image was not booted. Press any key to reboot.\r\n"));
+    });
+
+    uefi::system::with_stdin(|stdin| {
+        let _ = stdin.reset(false);
+        while !matches!(stdin.read_key(), Ok(Some(_))) {
+            uefi::boot::stall(core::time::Duration::from_millis(100));
+        }
+    });
+
+    reboot_system();
+}
+
+/// Pick a boot entry, load its kernel and initrd and enter the kernel
 fn load_and_start_kernel() -> uefi::Result {
+    // Claim the crash log region before any other allocation can land on it
+    if handoff::reserve_crashlog() {
+        uefi::system::with_stdout(|stdout| {
+            let _ = stdout.output_string(cstr16!("  - Crash log region reserved\r\n"));
+        });
+    }
+
     // Get the loaded image protocol to find our device
     let image_handle = uefi::boot::image_handle();
 
@@ -712,215 +508,136 @@ fn load_and_start_kernel() -> uefi::Result {
         let _ = stdout.output_string(cstr16!("  - Opened EFI volume\r\n"));
     });
 
-    // Try to open kernel.efi
-    let kernel_path = cstr16!("\\EFI\\Rustux\\kernel.efi");
-    let kernel_file = root.open(kernel_path, FileMode::Read, FileAttribute::empty());
+    let entry = menu::select(config::load(&mut root));
 
-    match kernel_file {
-        Ok(handle) => {
-            uefi::system::with_stdout(|stdout| {
-                let _ = stdout.output_string(cstr16!("  - Found kernel.efi, loading...\r\n"));
-            });
+    let policy = match secureboot::policy() {
+        Ok(policy) => policy,
+        Err(e) => show_verification_error(&entry.kernel, e),
+    };
+    let mut boot_flags = match policy {
+        secureboot::Policy::Verify(_) => handoff::HANDOFF_FLAG_VERIFIED,
+        secureboot::Policy::Unverified => 0,
+    };
+    if secureboot::firmware_enabled() {
+        boot_flags |= handoff::HANDOFF_FLAG_SECURE_BOOT;
+    }
 
-            match handle.into_type().map_err(|e| e.status())? {
-                uefi::proto::media::file::FileType::Regular(mut file) => {
-                    // Read file info
-                    let mut info_buf = [0u8; 256];
-                    let info = file.get_info::<uefi::proto::media::file::FileInfo>(&mut info_buf)
-                        .map_err(|e| uefi::Error::from(e.status()))?;
-
-                    let file_size = info.file_size() as usize;
-
-                    uefi::system::with_stdout(|stdout| {
-                        let _ = stdout.output_string(cstr16!("  - Kernel file size: "));
-                        // Simple size display - just show OK for now
-                        let _ = stdout.output_string(cstr16!("OK\r\n"));
-                    });
-
-                    // Allocate memory for the kernel
-                    let num_pages = (file_size + 0xFFF) / 0x1000;
-                    let kernel_data = uefi::boot::allocate_pages(
-                        AllocateType::AnyPages,
-                        MemoryType::LOADER_DATA,
-                        num_pages,
-                    )?;
-
-                    // Read the file
-                    let kernel_slice = unsafe {
-                        core::slice::from_raw_parts_mut(kernel_data.as_ptr(), file_size)
-                    };
-                    file.read(kernel_slice).map_err(|e| uefi::Error::from(e.status()))?;
-
-                    uefi::system::with_stdout(|stdout| {
-                        let _ = stdout.output_string(cstr16!("  - Kernel loaded into memory\r\n"));
-                    });
-
-                    // Validate PE/COFF header before calling LoadImage
-                    let kernel_ptr = kernel_data.as_ptr() as *const u8;
-                    if let Err(_e) = validate_pe_coff(kernel_ptr, file_size) {
-                        uefi::system::with_stdout(|stdout| {
-                            let _ = stdout.output_string(cstr16!("  - PE/COFF validation FAILED\r\n"));
-                            let _ = stdout.output_string(cstr16!("  - Trying LoadImage anyway...\r\n"));
-                        });
-                    }
+    uefi::system::with_stdout(|stdout| {
+        let _ = write!(stdout, "  - Booting \"{}\"\r\n  - Loading {}\r\n", entry.title, entry.kernel);
+    });
 
-                    uefi::system::with_stdout(|stdout| {
-                        let _ = stdout.output_string(cstr16!("  - Calling LoadImage...\r\n"));
-                    });
-
-                    // Load and start the kernel as an EFI image using raw boot services
-                    // Try LoadImage first, fall back to direct entry point call
-                    let result = unsafe {
-                        let bt = uefi::table::system_table_raw().unwrap();
-                        let system_table = bt.as_ref();
-                        let boot_services = system_table.boot_services;
-
-                        let mut kernel_handle: *mut core::ffi::c_void = core::ptr::null_mut();
-                        let load_image = (*boot_services).load_image;
-
-                        // Call LoadImage with parameters matching UEFI spec:
-                        // EFI_STATUS LoadImage(
-                        //   IN BOOLEAN BootPolicy, TRUE = load from FilePath, FALSE = load from SourceBuffer
-                        //   IN EFI_HANDLE ParentImageHandle,
-                        //   IN EFI_DEVICE_PATH_PROTOCOL *FilePath, OPTIONAL
-                        //   IN VOID *SourceBuffer, OPTIONAL
-                        //   IN UINTN SourceSize,
-                        //   OUT EFI_HANDLE *ImageHandle
-                        // );
-                        let status = load_image(
-                            false.into(),  // BootPolicy: load from memory buffer
-                            uefi::boot::image_handle().as_ptr(),
-                            core::ptr::null(),  // No FilePath
-                            kernel_data.as_ptr() as *mut u8,
-                            file_size,
-                            &mut kernel_handle,
-                        );
-
-                        uefi::system::with_stdout(|stdout| {
-                            let _ = stdout.output_string(cstr16!("  - LoadImage status: "));
-                            match status {
-                                Status::SUCCESS => {
-                                    let _ = stdout.output_string(cstr16!("SUCCESS\r\n"));
-                                }
-                                Status::LOAD_ERROR => {
-                                    let _ = stdout.output_string(cstr16!("LOAD_ERROR\r\n"));
-                                }
-                                Status::INVALID_PARAMETER => {
-                                    let _ = stdout.output_string(cstr16!("INVALID_PARAMETER\r\n"));
-                                }
-                                Status::UNSUPPORTED => {
-                                    let _ = stdout.output_string(cstr16!("UNSUPPORTED\r\n"));
-                                }
-                                Status::BUFFER_TOO_SMALL => {
-                                    let _ = stdout.output_string(cstr16!("BUFFER_TOO_SMALL\r\n"));
-                                }
-                                _ => {
-                                    // Display hex error code
-                                    let code = status.0;
-                                    let _ = stdout.output_string(cstr16!("UNKNOWN(0x"));
-                                    // Simple hex display
-                                    let hex_chars = [cstr16!("0"), cstr16!("1"), cstr16!("2"), cstr16!("3"),
-                                                    cstr16!("4"), cstr16!("5"), cstr16!("6"), cstr16!("7"),
-                                                    cstr16!("8"), cstr16!("9"), cstr16!("A"), cstr16!("B"),
-                                                    cstr16!("C"), cstr16!("D"), cstr16!("E"), cstr16!("F")];
-                                    let hi = (code >> 12) & 0xF;
-                                    let mid_hi = (code >> 8) & 0xF;
-                                    let mid_lo = (code >> 4) & 0xF;
-                                    let lo = code & 0xF;
-                                    let _ = stdout.output_string(hex_chars[hi as usize]);
-                                    let _ = stdout.output_string(hex_chars[mid_hi as usize]);
-                                    let _ = stdout.output_string(hex_chars[mid_lo as usize]);
-                                    let _ = stdout.output_string(hex_chars[lo as usize]);
-                                    let _ = stdout.output_string(cstr16!(")\r\n"));
-
-                                    // Show common error codes
-                                    let _ = stdout.output_string(cstr16!("    (0x8001=LOAD_ERROR, 0x8002=INVALID_PARAM)\r\n"));
-                                }
-                            }
-                        });
-
-                        if !status.is_success() {
-                            uefi::system::with_stdout(|stdout| {
-                                let _ = stdout.output_string(cstr16!("  - LoadImage failed, trying direct entry point...\r\n"));
-                            });
-
-                            // Fallback: Find and call the EFI entry point directly
-                            // PE/COFF format: entry point is at offset 0x28 in PE header
-                            let dos_header = kernel_data.as_ptr() as *const u8;
-                            let pe_offset = *(dos_header.add(0x3C) as *const u32) as usize;
-                            let pe_header = dos_header.add(pe_offset);
-                            let optional_header_offset = pe_offset + 0x18;
-                            let entry_point_rva = *(pe_header.add(optional_header_offset + 0x10) as *const u32) as usize;
-                            let image_base = kernel_data.as_ptr() as usize;
-                            let entry_point = (image_base + entry_point_rva) as *const ();
-
-                            uefi::system::with_stdout(|stdout| {
-                                let _ = stdout.output_string(cstr16!("  - Jumping to kernel entry point...\r\n"));
-                            });
-
-                            // UEFI entry point signature: fn(image_handle: Handle, system_table: *mut SystemTable) -> Status
-                            type EfiEntry = extern "efiapi" fn(*mut core::ffi::c_void, *mut uefi_raw::table::system::SystemTable) -> Status;
-                            let efi_entry: EfiEntry = core::mem::transmute(entry_point);
-
-                            let entry_status = efi_entry(
-                                uefi::boot::image_handle().as_ptr(),
-                                system_table as *const _ as *mut _
-                            );
-
-                            uefi::system::with_stdout(|stdout| {
-                                let _ = stdout.output_string(cstr16!("  - Kernel entry returned: "));
-                                match entry_status {
-                                    Status::SUCCESS => {
-                                        let _ = stdout.output_string(cstr16!("SUCCESS\r\n"));
-                                    }
-                                    _ => {
-                                        let _ = stdout.output_string(cstr16!("FAILED\r\n"));
-                                    }
-                                }
-                            });
-
-                            Err(entry_status)
-                        } else {
-                            uefi::system::with_stdout(|stdout| {
-                                let _ = stdout.output_string(cstr16!("  - Calling StartImage...\r\n"));
-                            });
-
-                            let start_image = (*boot_services).start_image;
-                            let status = start_image(
-                                kernel_handle,
-                                core::ptr::null_mut(),
-                                core::ptr::null_mut(),
-                            );
-
-                            uefi::system::with_stdout(|stdout| {
-                                let _ = stdout.output_string(cstr16!("  - StartImage status: "));
-                                match status {
-                                    Status::SUCCESS => {
-                                        let _ = stdout.output_string(cstr16!("SUCCESS\r\n"));
-                                    }
-                                    _ => {
-                                        let _ = stdout.output_string(cstr16!("FAILED\r\n"));
-                                    }
-                                }
-                            });
-
-                            if status.is_success() {
-                                Err(uefi::Status::ABORTED)
-                            } else {
-                                Err(status)
-                            }
-                        }
-                    };
-
-                    result.map_err(|e| uefi::Error::from(e))
+    let kernel_bytes = match load_file(&mut root, &entry.kernel) {
+        Ok(data) => data,
+        Err(_) => show_error_menu("Error: kernel not found"),
+    };
+
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.output_string(cstr16!("  - Kernel loaded into memory\r\n"));
+    });
+
+    if let Err(e) = elf::validate(kernel_bytes) {
+        uefi::system::with_stdout(|stdout| {
+            let _ = stdout.output_string(cstr16!("  - ELF validation FAILED\r\n"));
+        });
+        show_error_menu(e);
+    }
+
+    if let Err(e) = secureboot::verify(policy, &mut root, &entry.kernel, kernel_bytes) {
+        show_verification_error(&entry.kernel, e);
+    }
+    if let secureboot::Policy::Verify(_) = policy {
+        uefi::system::with_stdout(|stdout| {
+            let _ = stdout.output_string(cstr16!("  - Kernel signature verified\r\n"));
+        });
+    }
+
+    // Measurements are best effort: without them the kernel just isn't
+    // told it was measured
+    let mut tpm = tpm::Tpm::open();
+    let mut measured = tpm.is_some();
+    if let Some(tpm) = tpm.as_mut() {
+        let description = alloc::format!("kernel {}", entry.kernel);
+        measured &= tpm.measure(tpm::PCR_IMAGES, kernel_bytes, &description).is_ok();
+    }
+
+    let initrd = match &entry.initrd {
+        Some(path) => match load_file(&mut root, path) {
+            Ok(data) => {
+                if let Err(e) = secureboot::verify(policy, &mut root, path, data) {
+                    show_verification_error(path, e);
                 }
-                _ => {
-                    show_error_menu("Error: kernel.efi is not a regular file");
+                if let Some(tpm) = tpm.as_mut() {
+                    let description = alloc::format!("initrd {}", path);
+                    measured &= tpm.measure(tpm::PCR_IMAGES, data, &description).is_ok();
                 }
+                Some((&*data, path.as_str()))
+            }
+            Err(_) => {
+                uefi::system::with_stdout(|stdout| {
+                    let _ = write!(stdout, "  - Failed to load initrd {}\r\n", path);
+                });
+                show_error_menu("Error: initrd not found");
+            }
+        },
+        None => None,
+    };
+
+    // The entry's command line, then anything passed in the load options
+    let mut cmdline = entry.cmdline.clone();
+    if let Some(options) = loaded_image.load_options_as_bytes() {
+        let options = config::options_to_ascii(options);
+        if !options.is_empty() {
+            if !cmdline.is_empty() {
+                cmdline.push(' ');
             }
+            cmdline.push_str(&options);
         }
-        Err(_e) => {
-            show_error_menu("Error: kernel.efi not found at /EFI/Rustux/kernel.efi");
+    }
+
+    if let Some(tpm) = tpm.as_mut() {
+        let description = alloc::format!("cmdline {}", cmdline);
+        measured &= tpm.measure(tpm::PCR_CMDLINE, cmdline.as_bytes(), &description).is_ok();
+    }
+
+    // Boot randomness: the kernel CPRNG seed, then the load address slot
+    let mut random = [0u8; handoff::HANDOFF_RNG_SEED_MAX + 8];
+    let have_rng = kaslr::fill_random(&mut random);
+    let (rng_seed, slot_seed) = random.split_at(handoff::HANDOFF_RNG_SEED_MAX);
+    let kaslr_seed = if kaslr::enabled(cmdline.as_bytes()) {
+        Some(u64::from_le_bytes(slot_seed.try_into().unwrap()))
+    } else {
+        None
+    };
+
+    let kernel = match elf::load(kernel_bytes, kaslr_seed) {
+        Ok(kernel) => kernel,
+        Err(e) => {
+            uefi::system::with_stdout(|stdout| {
+                let _ = stdout.output_string(cstr16!("  - Kernel image load FAILED\r\n"));
+            });
+            show_error_menu(e);
         }
+    };
+
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.output_string(cstr16!("  - Kernel image loaded and relocated\r\n"));
+    });
+
+    // Unconfirmed until the OS marks it good
+    config::set_pending_entry(&entry);
+
+    let tpm_log = tpm.as_mut().and_then(|tpm| tpm.copy_event_log());
+    if measured {
+        boot_flags |= handoff::HANDOFF_FLAG_MEASURED;
+        uefi::system::with_stdout(|stdout| {
+            let _ = stdout.output_string(cstr16!("  - Kernel, initrd and cmdline measured into the TPM\r\n"));
+        });
+    } else if tpm.is_some() {
+        uefi::system::with_stdout(|stdout| {
+            let _ = stdout.output_string(cstr16!("  - Warning: TPM measurement failed\r\n"));
+        });
     }
+
+    let seed = if have_rng { Some(rng_seed) } else { None };
+    handoff_to_kernel(&kernel, initrd, &cmdline, seed, boot_flags, tpm_log)
 }
@@ -0,0 +1,218 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot menu
+//!
+//! Lists the configured entries on the firmware text console, followed by
+//! the last known good entry when the OS has recorded one. Up and Down
+//! move the selection, Enter boots it and a digit boots that entry. The
+//! default boots when the timeout runs out; any key stops the countdown.
+//!
+//! With a zero timeout the default boots without showing the menu, unless
+//! the previous boot failed.
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::time::Duration;
+
+use uefi::proto::console::text::{Key, ScanCode};
+
+use crate::config::{self, BootConfig, BootEntry};
+
+/// Key polling interval
+const POLL_INTERVAL_MS: u64 = 100;
+
+/// Console row of the status line
+const STATUS_ROW: usize = 4;
+
+/// Draw the menu with `selected` highlighted
+fn draw(entries: &[BootEntry], selected: usize, failed: Option<&BootEntry>) {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.clear();
+        let _ = write!(stdout, "Rustux Boot Menu\r\n\r\n");
+        if let Some(failed) = failed {
+            let _ = write!(stdout, "The previous boot of \"{}\" did not complete.\r\n", failed.title);
+        }
+
+        let _ = stdout.set_cursor_position(0, STATUS_ROW + 2);
+        for (i, entry) in entries.iter().enumerate() {
+            let marker = if i == selected { '>' } else { ' ' };
+            let _ = write!(stdout, "  {} {}. {}\r\n", marker, i + 1, entry.title);
+        }
+    });
+}
+
+/// Show how long until the selection boots
+fn draw_status(entry: &BootEntry, ticks_left: Option<u64>) {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.set_cursor_position(0, STATUS_ROW);
+        let line = match ticks_left {
+            Some(ticks) => {
+                let seconds = (ticks * POLL_INTERVAL_MS + 999) / 1000;
+                format!("Booting \"{}\" in {} s. Up/Down to select, Enter to boot.", entry.title, seconds)
+            }
+            None => format!("Up/Down to select \"{}\", Enter to boot.", entry.title),
+        };
+        let _ = write!(stdout, "{:<78}\r\n", line);
+    });
+}
+
+/// Read a key without waiting
+fn read_key() -> Option<Key> {
+    uefi::system::with_stdin(|stdin| stdin.read_key().ok().flatten())
+}
+
+/// Offer the last known good entry after the configured ones
+///
+/// An unconfirmed boot of anything but that entry means the previous boot
+/// failed, so it becomes the selection. Returns the selection and the
+/// entry that failed.
+fn add_last_good(
+    entries: &mut Vec<BootEntry>,
+    selected: usize,
+    mut good: BootEntry,
+    pending: Option<BootEntry>,
+) -> (usize, Option<BootEntry>) {
+    let pending = pending.filter(|pending| *pending != good);
+    good.title = format!("Last known good: {}", good.title);
+    entries.push(good);
+    match pending {
+        Some(failed) => (entries.len() - 1, Some(failed)),
+        None => (selected, None),
+    }
+}
+
+/// Selection after `key` among `count` entries, and whether to boot it
+fn apply_key(key: &Key, selected: usize, count: usize) -> (usize, bool) {
+    match key {
+        Key::Special(ScanCode::UP) => (selected.checked_sub(1).unwrap_or(count - 1), false),
+        Key::Special(ScanCode::DOWN) => ((selected + 1) % count, false),
+        Key::Printable(c) => match char::from(*c) {
+            '\r' | '\n' => (selected, true),
+            c @ '1'..='9' if (c as usize - '1' as usize) < count => (c as usize - '1' as usize, true),
+            _ => (selected, false),
+        },
+        _ => (selected, false),
+    }
+}
+
+/// Let the user pick the entry to boot
+pub fn select(config: BootConfig) -> BootEntry {
+    let mut entries: Vec<BootEntry> = config.entries;
+    let (mut selected, failed) = match config::last_good_entry() {
+        Some(good) => add_last_good(&mut entries, config.default, good, config::pending_entry()),
+        None => (config.default, None),
+    };
+
+    if config.timeout == 0 && failed.is_none() {
+        return entries.swap_remove(selected);
+    }
+
+    // Without a timeout after a failure, wait for the user
+    let mut ticks_left = match config.timeout {
+        0 => None,
+        seconds => Some(seconds as u64 * 1000 / POLL_INTERVAL_MS),
+    };
+
+    draw(&entries, selected, failed.as_ref());
+    loop {
+        draw_status(&entries[selected], ticks_left);
+
+        let key = loop {
+            if let Some(key) = read_key() {
+                break Some(key);
+            }
+            match ticks_left {
+                Some(0) => break None,
+                Some(ticks) => {
+                    uefi::boot::stall(Duration::from_millis(POLL_INTERVAL_MS));
+                    ticks_left = Some(ticks - 1);
+                    if (ticks - 1) % (1000 / POLL_INTERVAL_MS) == 0 {
+                        draw_status(&entries[selected], ticks_left);
+                    }
+                }
+                None => uefi::boot::stall(Duration::from_millis(POLL_INTERVAL_MS)),
+            }
+        };
+
+        let key = match key {
+            Some(key) => key,
+            None => break,
+        };
+        ticks_left = None;
+
+        let (next, boot) = apply_key(&key, selected, entries.len());
+        selected = next;
+        if boot {
+            break;
+        }
+        draw(&entries, selected, failed.as_ref());
+    }
+
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.clear();
+    });
+    entries.swap_remove(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+    use uefi::Char16;
+
+    fn entry(title: &str) -> BootEntry {
+        BootEntry {
+            title: String::from(title),
+            kernel: format!("\\{}.elf", title),
+            initrd: None,
+            cmdline: String::new(),
+        }
+    }
+
+    fn printable(c: char) -> Key {
+        Key::Printable(Char16::try_from(c).unwrap())
+    }
+
+    #[test]
+    fn test_apply_key() {
+        // Up and Down wrap around
+        assert_eq!(apply_key(&Key::Special(ScanCode::UP), 0, 3), (2, false));
+        assert_eq!(apply_key(&Key::Special(ScanCode::DOWN), 2, 3), (0, false));
+        assert_eq!(apply_key(&Key::Special(ScanCode::DOWN), 0, 3), (1, false));
+
+        assert_eq!(apply_key(&printable('\r'), 1, 3), (1, true));
+        assert_eq!(apply_key(&printable('3'), 0, 3), (2, true));
+
+        // Digits past the last entry, and other keys, do nothing
+        assert_eq!(apply_key(&printable('4'), 0, 3), (0, false));
+        assert_eq!(apply_key(&printable('0'), 1, 3), (1, false));
+        assert_eq!(apply_key(&printable('x'), 1, 3), (1, false));
+        assert_eq!(apply_key(&Key::Special(ScanCode::ESCAPE), 1, 3), (1, false));
+    }
+
+    #[test]
+    fn test_last_good_after_confirmed_boot() {
+        let mut entries = vec![entry("a"), entry("b")];
+
+        // Nothing pending, or the last good entry itself pending
+        assert_eq!(add_last_good(&mut entries, 1, entry("a"), None), (1, None));
+        assert_eq!(entries[2].title, "Last known good: a");
+        assert_eq!(entries[2].kernel, "\\a.elf");
+
+        let mut entries = vec![entry("a"), entry("b")];
+        assert_eq!(add_last_good(&mut entries, 1, entry("a"), Some(entry("a"))), (1, None));
+    }
+
+    #[test]
+    fn test_last_good_after_failed_boot() {
+        let mut entries = vec![entry("a"), entry("b")];
+        assert_eq!(add_last_good(&mut entries, 0, entry("a"), Some(entry("b"))), (2, Some(entry("b"))));
+        assert_eq!(entries.len(), 3);
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Initial page tables for entering the kernel
+//!
+//! The kernel is entered with 4-level paging on tables built here:
+//! - physical memory identity mapped with 2 MiB pages, so the handoff
+//!   block, boot stack, ACPI tables and framebuffer stay reachable at
+//!   their physical addresses
+//! - the kernel segments at their virtual addresses with 4 KiB pages,
+//!   writable only where the segment is, unless the image runs where it
+//!   was loaded and the identity map already covers it
+//!
+//! The identity map covers the low 4 GiB (local APIC and other MMIO),
+//! everything in the firmware memory map and the framebuffer, up to
+//! 512 GiB (one PML4 entry). The tables live in LOADER_DATA pages and are
+//! only loaded into CR3 at the jump; the kernel replaces them during early
+//! MMU setup.
+
+use uefi::boot::{AllocateType, MemoryType};
+
+use crate::elf::LoadedKernel;
+use crate::handoff::EfiMemoryMap;
+
+const PAGE_SIZE: u64 = 0x1000;
+const GIB: u64 = 1 << 30;
+
+/// Page table entry bits
+const PTE_P: u64 = 1 << 0;
+const PTE_W: u64 = 1 << 1;
+const PTE_PS: u64 = 1 << 7;
+const PTE_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Lowest and highest end of the identity map
+const IDENTITY_MIN: u64 = 4 * GIB;
+const IDENTITY_MAX: u64 = 512 * GIB;
+
+/// CR4.LA57: the firmware runs with 5-level paging
+const CR4_LA57: u64 = 1 << 12;
+
+/// Page tables for the jump to the kernel
+pub struct PageTables {
+    /// Physical address of the PML4, for CR3
+    pml4: u64,
+    /// Preallocated pages the tables are taken from
+    pool: u64,
+    pool_pages: u64,
+    used: u64,
+}
+
+impl PageTables {
+    /// Value to load into CR3
+    pub fn root(&self) -> u64 {
+        self.pml4
+    }
+
+    fn alloc_table(&mut self) -> Result<u64, &'static str> {
+        if self.used == self.pool_pages {
+            return Err("Page table pool exhausted");
+        }
+        let table = self.pool + self.used * PAGE_SIZE;
+        self.used += 1;
+        Ok(table)
+    }
+
+    /// Table referenced by entry `index` of `table`, created if missing
+    unsafe fn next_table(&mut self, table: u64, index: u64) -> Result<u64, &'static str> {
+        let entry = (table as *mut u64).add(index as usize);
+        if *entry & PTE_P == 0 {
+            *entry = self.alloc_table()? | PTE_P | PTE_W;
+        } else if *entry & PTE_PS != 0 {
+            return Err("Kernel mapping overlaps the identity map");
+        }
+        Ok(*entry & PTE_ADDR_MASK)
+    }
+
+    /// Map one 4 KiB page
+    unsafe fn map_page(&mut self, virt: u64, phys: u64, flags: u64) -> Result<(), &'static str> {
+        let pdpt = self.next_table(self.pml4, (virt >> 39) & 0x1FF)?;
+        let pd = self.next_table(pdpt, (virt >> 30) & 0x1FF)?;
+        let pt = self.next_table(pd, (virt >> 21) & 0x1FF)?;
+        let entry = (pt as *mut u64).add(((virt >> 12) & 0x1FF) as usize);
+        if *entry & PTE_P != 0 {
+            return Err("Kernel segments overlap");
+        }
+        *entry = phys | flags;
+        Ok(())
+    }
+
+    /// Identity map `[0, limit)` with 2 MiB pages through PML4 entry 0
+    unsafe fn map_identity(&mut self, limit: u64) -> Result<(), &'static str> {
+        for gib in 0..limit / GIB {
+            let pdpt = self.next_table(self.pml4, 0)?;
+            let pd = self.next_table(pdpt, gib)?;
+            for i in 0..512 {
+                let phys = gib * GIB + i * 2 * 1024 * 1024;
+                *(pd as *mut u64).add(i as usize) = phys | PTE_P | PTE_W | PTE_PS;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// End of the identity map: all memory the firmware reports and the
+/// framebuffer, rounded up to 1 GiB
+fn identity_limit(framebuffer_end: u64) -> Result<u64, &'static str> {
+    let mut map = EfiMemoryMap::allocate().map_err(|_| "Failed to read memory map")?;
+    map.refresh().map_err(|_| "Failed to read memory map")?;
+
+    let mut end = IDENTITY_MIN.max(framebuffer_end);
+    for i in 0..map.entry_count() {
+        let desc = map.descriptor(i);
+        end = end.max(desc.phys_start + desc.page_count * PAGE_SIZE);
+    }
+
+    Ok(((end + GIB - 1) & !(GIB - 1)).min(IDENTITY_MAX))
+}
+
+/// Build the page tables the kernel is entered on
+///
+/// `framebuffer_end` is the physical end of the framebuffer, or 0.
+pub fn build(kernel: &LoadedKernel, framebuffer_end: u64) -> Result<PageTables, &'static str> {
+    let cr4: u64;
+    unsafe { core::arch::asm!("mov {}, cr4", out(reg) cr4, options(nomem, nostack)) };
+    if cr4 & CR4_LA57 != 0 {
+        return Err("5-level paging is not supported");
+    }
+
+    let limit = identity_limit(framebuffer_end)?;
+    let identity = |virt: u64, phys: u64, size: u64| virt == phys && phys + size <= limit;
+
+    // PML4 and identity PDPT, one PD per GiB, and for each kernel segment
+    // its page tables plus the directories either side of a boundary
+    let mut pool_pages = 2 + limit / GIB;
+    for segment in kernel.segments() {
+        if !identity(segment.virt, segment.phys, segment.size) {
+            pool_pages += segment.size / (512 * PAGE_SIZE) + 6;
+        }
+    }
+
+    let pool = uefi::boot::allocate_pages(
+        AllocateType::AnyPages,
+        MemoryType::LOADER_DATA,
+        pool_pages as usize,
+    )
+    .map_err(|_| "Failed to allocate page tables")?
+    .as_ptr();
+    unsafe { core::ptr::write_bytes(pool, 0, (pool_pages * PAGE_SIZE) as usize) };
+
+    let mut tables = PageTables {
+        pml4: pool as u64,
+        pool: pool as u64,
+        pool_pages,
+        used: 1,
+    };
+
+    unsafe {
+        tables.map_identity(limit)?;
+
+        for segment in kernel.segments() {
+            if identity(segment.virt, segment.phys, segment.size) {
+                continue;
+            }
+            if segment.virt < limit {
+                return Err("Kernel virtual range overlaps the identity map");
+            }
+
+            let flags = if segment.writable { PTE_P | PTE_W } else { PTE_P };
+            for offset in (0..segment.size).step_by(PAGE_SIZE as usize) {
+                tables.map_page(segment.virt + offset, segment.phys + offset, flags)?;
+            }
+        }
+    }
+
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[repr(C, align(4096))]
+    #[derive(Clone, Copy)]
+    struct Table([u64; 512]);
+
+    /// Tables built in `pool`, whose addresses stand in for physical ones
+    fn tables(pool: &mut [Table]) -> PageTables {
+        let base = pool.as_mut_ptr() as u64;
+        PageTables { pml4: base, pool: base, pool_pages: pool.len() as u64, used: 1 }
+    }
+
+    fn entry(table: u64, index: u64) -> u64 {
+        unsafe { *(table as *const u64).add(index as usize) }
+    }
+
+    /// Leaf entry mapping `virt`, walking from the PML4
+    fn lookup(tables: &PageTables, virt: u64) -> u64 {
+        let pdpt = entry(tables.pml4, (virt >> 39) & 0x1FF) & PTE_ADDR_MASK;
+        let pd = entry(pdpt, (virt >> 30) & 0x1FF) & PTE_ADDR_MASK;
+        let pt = entry(pd, (virt >> 21) & 0x1FF) & PTE_ADDR_MASK;
+        entry(pt, (virt >> 12) & 0x1FF)
+    }
+
+    #[test]
+    fn test_map_identity() {
+        let mut pool: Vec<Table> = vec![Table([0; 512]); 4];
+        let mut tables = tables(&mut pool);
+        unsafe { tables.map_identity(2 * GIB) }.unwrap();
+
+        // One PDPT and a PD per GiB
+        assert_eq!(tables.used, 4);
+        let pdpt = entry(tables.pml4, 0) & PTE_ADDR_MASK;
+        let pd = entry(pdpt, 1) & PTE_ADDR_MASK;
+        assert_eq!(entry(pd, 0), GIB | PTE_P | PTE_W | PTE_PS);
+        assert_eq!(entry(pd, 511), GIB + 511 * 2 * 1024 * 1024 | PTE_P | PTE_W | PTE_PS);
+        assert_eq!(entry(pdpt, 2), 0);
+    }
+
+    #[test]
+    fn test_map_page() {
+        let mut pool: Vec<Table> = vec![Table([0; 512]); 8];
+        let mut tables = tables(&mut pool);
+        let virt = 0xFFFF_FFFF_8000_0000;
+        unsafe {
+            tables.map_page(virt, 0x20_0000, PTE_P).unwrap();
+            tables.map_page(virt + PAGE_SIZE, 0x20_1000, PTE_P | PTE_W).unwrap();
+        }
+
+        // Neighbouring pages share their tables
+        assert_eq!(tables.used, 4);
+        assert_eq!(lookup(&tables, virt), 0x20_0000 | PTE_P);
+        assert_eq!(lookup(&tables, virt + PAGE_SIZE), 0x20_1000 | PTE_P | PTE_W);
+
+        assert_eq!(unsafe { tables.map_page(virt, 0x30_0000, PTE_P) }, Err("Kernel segments overlap"));
+    }
+
+    #[test]
+    fn test_map_page_rejects_identity_range() {
+        let mut pool: Vec<Table> = vec![Table([0; 512]); 8];
+        let mut tables = tables(&mut pool);
+        unsafe {
+            tables.map_identity(GIB).unwrap();
+            assert_eq!(
+                tables.map_page(0x20_0000, 0x20_0000, PTE_P),
+                Err("Kernel mapping overlaps the identity map")
+            );
+        }
+    }
+
+    #[test]
+    fn test_pool_exhausted() {
+        let mut pool: Vec<Table> = vec![Table([0; 512]); 3];
+        let mut tables = tables(&mut pool);
+        assert_eq!(
+            unsafe { tables.map_page(0xFFFF_FFFF_8000_0000, 0, PTE_P) },
+            Err("Page table pool exhausted")
+        );
+    }
+}
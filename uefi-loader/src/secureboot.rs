@@ -0,0 +1,143 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Verified boot
+//!
+//! The kernel and initrd of the chosen entry are checked against detached
+//! Ed25519 signatures before the loader enters them: `kernel.elf` is
+//! signed by `kernel.elf.sig` next to it, holding the 64 raw signature
+//! bytes `scripts/sign-boot-image.py` writes.
+//!
+//! The public key is embedded at build time from the `RUSTUX_BOOT_PUBKEY`
+//! environment variable (64 hex digits):
+//!
+//! ```text
+//! RUSTUX_BOOT_PUBKEY=$(scripts/sign-boot-image.py pubkey boot.key) cargo build ...
+//! ```
+//!
+//! With a key, every image must carry a valid signature. Without one the
+//! loader boots unverified, unless the firmware has Secure Boot enabled:
+//! a loader the firmware trusts must not then hand control to images it
+//! cannot check.
+
+use alloc::string::String;
+
+use uefi::proto::media::file::Directory;
+use uefi::runtime::VariableVendor;
+use uefi::cstr16;
+
+use crate::config;
+use crate::ed25519::{self, PUBLIC_KEY_SIZE, SIGNATURE_SIZE};
+
+/// Suffix of the signature file next to each image
+const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Public key embedded at build time, if any
+const PUBLIC_KEY: Option<[u8; PUBLIC_KEY_SIZE]> = match option_env!("RUSTUX_BOOT_PUBKEY") {
+    Some(hex) => Some(parse_key(hex)),
+    None => None,
+};
+
+/// Decode the hex public key, failing the build if it is malformed
+const fn parse_key(hex: &str) -> [u8; PUBLIC_KEY_SIZE] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            b'A'..=b'F' => c - b'A' + 10,
+            _ => panic!("RUSTUX_BOOT_PUBKEY must be hex"),
+        }
+    }
+
+    let hex = hex.as_bytes();
+    if hex.len() != PUBLIC_KEY_SIZE * 2 {
+        panic!("RUSTUX_BOOT_PUBKEY must be 64 hex digits");
+    }
+    let mut key = [0u8; PUBLIC_KEY_SIZE];
+    let mut i = 0;
+    while i < PUBLIC_KEY_SIZE {
+        key[i] = nibble(hex[2 * i]) << 4 | nibble(hex[2 * i + 1]);
+        i += 1;
+    }
+    key
+}
+
+/// What the loader checks before entering the kernel
+#[derive(Clone, Copy)]
+pub enum Policy {
+    /// Every image must be signed by this key
+    Verify(&'static [u8; PUBLIC_KEY_SIZE]),
+    /// No key embedded and Secure Boot off: images are not checked
+    Unverified,
+}
+
+/// Whether the firmware is enforcing UEFI Secure Boot
+pub fn firmware_enabled() -> bool {
+    let mut buf = [0u8; 1];
+    match uefi::runtime::get_variable(cstr16!("SecureBoot"), &VariableVendor::GLOBAL_VARIABLE, &mut buf) {
+        Ok((data, _)) => data == [1],
+        Err(_) => false,
+    }
+}
+
+/// Decide the policy for this boot
+///
+/// Fails when the firmware enforces Secure Boot but no key is embedded.
+pub fn policy() -> Result<Policy, &'static str> {
+    match &PUBLIC_KEY {
+        Some(key) => Ok(Policy::Verify(key)),
+        None if firmware_enabled() => Err("Secure Boot is enabled but the loader has no public key"),
+        None => Ok(Policy::Unverified),
+    }
+}
+
+/// Check `data`, loaded from `path` on the volume, against its signature
+pub fn verify(policy: Policy, root: &mut Directory, path: &str, data: &[u8]) -> Result<(), &'static str> {
+    let key = match policy {
+        Policy::Verify(key) => key,
+        Policy::Unverified => return Ok(()),
+    };
+
+    let mut sig_path = String::from(path);
+    sig_path.push_str(SIGNATURE_SUFFIX);
+    let sig_path = config::path_ucs2(&sig_path).ok_or("Invalid signature path")?;
+
+    let signature = config::read_file(root, &sig_path, SIGNATURE_SIZE).ok_or("Signature file missing")?;
+    let signature: &[u8; SIGNATURE_SIZE] = signature
+        .as_slice()
+        .try_into()
+        .map_err(|_| "Signature file malformed")?;
+
+    if ed25519::verify(key, signature, data) {
+        Ok(())
+    } else {
+        Err("Signature does not match")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let key = parse_key("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511A");
+        assert_eq!(key[..3], [0xd7, 0x5a, 0x98]);
+        assert_eq!(key[31], 0x1a);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be hex")]
+    fn test_parse_key_rejects_non_hex() {
+        parse_key("g75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+    }
+
+    #[test]
+    #[should_panic(expected = "must be 64 hex digits")]
+    fn test_parse_key_rejects_short_key() {
+        parse_key("d75a98");
+    }
+}
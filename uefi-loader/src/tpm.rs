@@ -0,0 +1,174 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TPM 2.0 measured boot
+//!
+//! Before entering the kernel the loader measures what it is about to
+//! run through the firmware's EFI_TCG2_PROTOCOL, following the PCR usage
+//! of other Linux-style loaders:
+//! - PCR 9: the kernel image, then the initrd, as read from the volume
+//! - PCR 8: the kernel command line
+//!
+//! Each measurement extends every active PCR bank and appends an EV_IPL
+//! event naming what was measured to the firmware event log.
+//!
+//! The firmware keeps the event log in boot services memory, so it is
+//! copied into LOADER_DATA pages for the kernel. Events logged after the
+//! copy, such as ExitBootServices, go to the final events table, whose
+//! address is passed along with the copy.
+
+use alloc::vec::Vec;
+
+use uefi::boot::{AllocateType, MemoryType};
+use uefi::proto::unsafe_protocol;
+use uefi::{Status, StatusExt};
+
+/// PCR of the kernel and initrd
+pub const PCR_IMAGES: u32 = 9;
+
+/// PCR of the kernel command line
+pub const PCR_CMDLINE: u32 = 8;
+
+/// EFI_TCG2_FINAL_EVENTS_TABLE_GUID
+pub const FINAL_EVENTS_TABLE_GUID: uefi::Guid = uefi::guid!("1e2ed096-30e2-4254-bd89-863bbef82325");
+
+/// EFI_TCG2_EVENT_LOG_FORMAT_TCG_2: the crypto-agile log
+const EVENT_LOG_FORMAT_TCG_2: u32 = 0x2;
+
+/// EV_IPL: event measured by the boot loader
+const EV_IPL: u32 = 0xD;
+
+/// Size of EFI_TCG2_EVENT_HEADER
+const EVENT_HEADER_SIZE: u32 = 14;
+const EVENT_HEADER_VERSION: u16 = 1;
+
+/// TCG algorithm IDs and digest sizes of the crypto-agile log
+const DIGEST_SIZES: [(u16, usize); 5] = [
+    (0x0004, 20), // SHA-1
+    (0x000B, 32), // SHA-256
+    (0x000C, 48), // SHA-384
+    (0x000D, 64), // SHA-512
+    (0x0012, 32), // SM3-256
+];
+
+/// EFI_TCG2_PROTOCOL
+#[repr(C)]
+#[unsafe_protocol("607f766c-7455-42be-930b-e4d76db2720f")]
+struct Tcg2 {
+    get_capability: usize,
+    get_event_log: unsafe extern "efiapi" fn(
+        this: *mut Tcg2,
+        format: u32,
+        location: *mut u64,
+        last_entry: *mut u64,
+        truncated: *mut u8,
+    ) -> Status,
+    hash_log_extend_event: unsafe extern "efiapi" fn(
+        this: *mut Tcg2,
+        flags: u64,
+        data: u64,
+        data_len: u64,
+        event: *const u8,
+    ) -> Status,
+    submit_command: usize,
+    get_active_pcr_banks: usize,
+    set_active_pcr_banks: usize,
+    get_result_of_set_active_pcr_banks: usize,
+}
+
+/// Event log copied out of boot services memory
+pub struct EventLog {
+    /// Physical address of the copy
+    pub base: u64,
+    /// Length in bytes
+    pub size: u64,
+}
+
+/// Measurements through the firmware TPM
+pub struct Tpm {
+    tcg: uefi::boot::ScopedProtocol<Tcg2>,
+}
+
+impl Tpm {
+    /// Open the TCG2 protocol, if the firmware has a TPM 2.0
+    pub fn open() -> Option<Self> {
+        let handle = uefi::boot::get_handle_for_protocol::<Tcg2>().ok()?;
+        let tcg = uefi::boot::open_protocol_exclusive::<Tcg2>(handle).ok()?;
+        Some(Self { tcg })
+    }
+
+    /// Hash `data` into `pcr` and log an EV_IPL event described by `description`
+    pub fn measure(&mut self, pcr: u32, data: &[u8], description: &str) -> uefi::Result {
+        // EFI_TCG2_EVENT: size, packed header, then the event data
+        let size = 4 + EVENT_HEADER_SIZE as usize + description.len();
+        let mut event = Vec::with_capacity(size);
+        event.extend_from_slice(&(size as u32).to_le_bytes());
+        event.extend_from_slice(&EVENT_HEADER_SIZE.to_le_bytes());
+        event.extend_from_slice(&EVENT_HEADER_VERSION.to_le_bytes());
+        event.extend_from_slice(&pcr.to_le_bytes());
+        event.extend_from_slice(&EV_IPL.to_le_bytes());
+        event.extend_from_slice(description.as_bytes());
+
+        let tcg: *mut Tcg2 = &mut *self.tcg;
+        let status = unsafe {
+            ((*tcg).hash_log_extend_event)(
+                tcg,
+                0,
+                data.as_ptr() as u64,
+                data.len() as u64,
+                event.as_ptr(),
+            )
+        };
+        status.to_result()
+    }
+
+    /// Copy the event log, including the loader's own measurements
+    pub fn copy_event_log(&mut self) -> Option<EventLog> {
+        let mut location = 0u64;
+        let mut last_entry = 0u64;
+        let mut truncated = 0u8;
+        let tcg: *mut Tcg2 = &mut *self.tcg;
+        let status = unsafe {
+            ((*tcg).get_event_log)(tcg, EVENT_LOG_FORMAT_TCG_2, &mut location, &mut last_entry, &mut truncated)
+        };
+        if status.is_error() || location == 0 || last_entry < location {
+            return None;
+        }
+
+        let size = last_entry - location + unsafe { event_size(last_entry, last_entry == location)? };
+        let pages = (size as usize + 0xFFF) / 0x1000;
+        let copy = uefi::boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages)
+            .ok()?
+            .as_ptr();
+        unsafe { core::ptr::copy_nonoverlapping(location as *const u8, copy, size as usize) };
+
+        Some(EventLog { base: copy as u64, size })
+    }
+}
+
+/// Size of the log entry at `addr`
+///
+/// The first entry is the specification ID event in the SHA-1 format
+/// (TCG_PCR_EVENT); the rest are crypto-agile TCG_PCR_EVENT2 records.
+unsafe fn event_size(addr: u64, first: bool) -> Option<u64> {
+    let read_u16 = |off: u64| core::ptr::read_unaligned((addr + off) as *const u16);
+    let read_u32 = |off: u64| core::ptr::read_unaligned((addr + off) as *const u32);
+
+    if first {
+        // PCR index, type, SHA-1 digest, event size, event
+        return Some(4 + 4 + 20 + 4 + read_u32(28) as u64);
+    }
+
+    // PCR index, type, TPML_DIGEST_VALUES, event size, event
+    let count = read_u32(8);
+    let mut off = 12u64;
+    for _ in 0..count {
+        let alg = read_u16(off);
+        let (_, digest_size) = DIGEST_SIZES.iter().find(|(id, _)| *id == alg)?;
+        off += 2 + *digest_size as u64;
+    }
+    Some(off + 4 + read_u32(off) as u64)
+}
@@ -0,0 +1,294 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Framebuffer Console
+//!
+//! The text console of the CLI boot mode. Takes the boot framebuffer over
+//! from the kernel's log console, runs a terminal emulator on it and
+//! attaches a shell.
+//!
+//! Keys come from the input service, which merges PS/2 and USB keyboards
+//! and the serial console into one stream of key events. They are turned
+//! into the bytes a terminal would send: Shift+PgUp and Shift+PgDn page
+//! through the scrollback; any other key returns to the live screen and
+//! goes to the shell.
+//!
+//! The console, the input service and the shell currently live in the
+//! same process: the service and the shell run on threads of their own
+//! and talk to the console over channels (key events from the service, a
+//! byte stream in each direction with the shell), so either can move to
+//! a process of its own without changing the console.
+//!
+//! Usage: `console`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate ipc;
+extern crate libddk;
+extern crate libsys;
+extern crate input;
+extern crate rt;
+
+#[path = "../../src/kernel/dev/fbcon/font.rs"]
+mod font;
+mod shell;
+mod terminal;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use input::InputService;
+use ipc::Channel;
+use libddk::input::terminal_bytes;
+use libddk::MmioBuffer;
+use libsys::handles::CACHE_POLICY_WRITE_COMBINING;
+use libsys::syscall::{syscall4, SyscallNumber};
+use libsys::framebuffer;
+use libsys::*;
+use rt::Thread;
+
+use terminal::{Framebuffer, Terminal};
+
+/// How long to sleep when there is nothing to do
+const POLL_INTERVAL: u64 = 5_000_000;
+
+/// Largest chunk of shell output handled at once
+const OUTPUT_CHUNK: usize = 4096;
+
+/// Terminal sequences for Shift+PgUp and Shift+PgDn
+const SCROLL_UP: &[u8] = b"\x1b[5;2~";
+const SCROLL_DOWN: &[u8] = b"\x1b[6;2~";
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Keyboard
+/// ============================================================================
+
+/// What a key does
+enum Key<'a> {
+    /// Bytes for the shell
+    Input(&'a [u8]),
+
+    /// Page the view back or forward
+    ScrollUp,
+    ScrollDown,
+}
+
+/// Picks the console's own keys out of the key input
+///
+/// An escape sequence is held back until it either is one of them or
+/// can't become one.
+struct KeyDecoder {
+    pending: [u8; 8],
+    len: usize,
+}
+
+impl KeyDecoder {
+    const fn new() -> Self {
+        Self { pending: [0; 8], len: 0 }
+    }
+
+    fn feed(&mut self, byte: u8, mut handle: impl FnMut(Key)) {
+        if self.len == 0 && byte != 0x1B {
+            handle(Key::Input(&[byte]));
+            return;
+        }
+
+        self.pending[self.len] = byte;
+        self.len += 1;
+        let seq = &self.pending[..self.len];
+        if seq == SCROLL_UP {
+            handle(Key::ScrollUp);
+        } else if seq == SCROLL_DOWN {
+            handle(Key::ScrollDown);
+        } else if SCROLL_UP.starts_with(seq) || SCROLL_DOWN.starts_with(seq) {
+            return;
+        } else {
+            handle(Key::Input(seq));
+        }
+        self.len = 0;
+    }
+
+    /// Pass on a held back sequence once input goes quiet, such as a lone
+    /// Esc
+    fn flush(&mut self, mut handle: impl FnMut(Key)) {
+        if self.len > 0 {
+            handle(Key::Input(&self.pending[..self.len]));
+            self.len = 0;
+        }
+    }
+}
+
+/// Act on a decoded key
+fn key_pressed(key: Key, term: &mut Terminal, shell: &Channel, page: isize) {
+    match key {
+        Key::ScrollUp => term.scroll_view(page),
+        Key::ScrollDown => term.scroll_view(-page),
+        Key::Input(bytes) => {
+            term.reset_view();
+            let _ = shell.write(bytes, &[]);
+        }
+    }
+}
+
+/// ============================================================================
+/// Console
+/// ============================================================================
+
+/// Map the framebuffer write-combining
+fn map_framebuffer(root: &Handle, info: &framebuffer::Info) -> Result<MmioBuffer> {
+    let size = (info.size as usize).next_multiple_of(4096);
+    let mut out: u32 = 0;
+    let ret = unsafe {
+        syscall4(
+            SyscallNumber::VmoCreatePhysical as u64,
+            root.raw() as u64,
+            info.base,
+            size as u64,
+            &mut out as *mut u32 as u64,
+        )
+    };
+    if (ret as i32) < 0 {
+        return Err(Error::from_raw(ret as i32));
+    }
+
+    let vmo = unsafe { Vmo::from_handle(Handle::from_raw(out, Rights::all())) };
+    vmo.set_cache_policy(CACHE_POLICY_WRITE_COMBINING)?;
+    MmioBuffer::map(vmo, 0, info.size as usize)
+}
+
+/// Input service thread entry point
+extern "C" fn input_main(arg: *mut u8) {
+    let mut service = unsafe { Box::from_raw(arg as *mut InputService) };
+    service.run();
+}
+
+/// Shell thread entry point
+extern "C" fn shell_main(arg: *mut u8) {
+    let (channel, root) = *unsafe { Box::from_raw(arg as *mut (Channel, Handle)) };
+    shell::run(&channel, root);
+}
+
+fn run(root: &Handle, log: &mut StdoutWriter) -> Result<()> {
+    let info = framebuffer::get_info(root, 0)?;
+    if info.format != framebuffer::FORMAT_RGB && info.format != framebuffer::FORMAT_BGR {
+        return Err(Error::new(Status::NotSupported));
+    }
+    let fb = map_framebuffer(root, &info)?;
+
+    // From here the kernel log only goes to the serial console
+    framebuffer::get_info(root, framebuffer::TAKE_OVER)?;
+    let mut term = unsafe {
+        Terminal::new(Framebuffer {
+            base: fb.as_ptr() as *mut u32,
+            width: info.width,
+            height: info.height,
+            stride: info.stride,
+            bgr: info.format == framebuffer::FORMAT_BGR,
+        })
+    };
+    let (cols, rows) = term.size();
+    let _ = writeln!(log, "console: {}x{} framebuffer, {}x{} text", info.width, info.height, cols, rows);
+
+    let mut service = InputService::start(root, log);
+    let keyboard = service.subscribe()?;
+    Thread::spawn(input_main, Box::into_raw(Box::new(service)) as *mut u8)?.detach();
+
+    let (shell_end, console_end) = Channel::create()?;
+    let shell_root = root.duplicate(Rights::SAME_RIGHTS)?;
+    Thread::spawn(shell_main, Box::into_raw(Box::new((shell_end, shell_root))) as *mut u8)?.detach();
+
+    let page = rows.saturating_sub(1).max(1) as isize;
+    let mut keys = KeyDecoder::new();
+    let mut output = [0u8; OUTPUT_CHUNK];
+    let mut handles = Vec::new();
+    loop {
+        let mut busy = false;
+
+        let events = input::protocol::read(&keyboard)?;
+        for event in &events {
+            let mut buf = [0u8; 8];
+            let len = terminal_bytes(event, &mut buf);
+            for &byte in &buf[..len] {
+                keys.feed(byte, |key| key_pressed(key, &mut term, &console_end, page));
+            }
+        }
+        if events.is_empty() {
+            keys.flush(|key| {
+                if let Key::Input(bytes) = key {
+                    let _ = console_end.write(bytes, &[]);
+                }
+            });
+        }
+        busy |= !events.is_empty();
+
+        match console_end.read(&mut output, &mut handles) {
+            Ok(len) if len > 0 => {
+                term.write(&output[..len]);
+                busy = true;
+            }
+            Err(e) if e.status() == Status::PeerClosed => {
+                let _ = writeln!(log, "console: shell exited");
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if busy {
+            term.render();
+        } else {
+            rt::timer::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(_argc: i32, _argv: *const *const u8) -> i32 {
+    let mut log = StdoutWriter;
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(log, "console: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&root, &mut log) {
+        Ok(()) => 0,
+        Err(e) if e.status() == Status::NotFound || e.status() == Status::NotSupported => {
+            let _ = writeln!(log, "console: no usable framebuffer");
+            1
+        }
+        Err(e) => {
+            let _ = writeln!(log, "console: {:?}", e);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
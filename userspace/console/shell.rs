@@ -0,0 +1,270 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Shell
+//!
+//! A line-oriented command interpreter on the far end of the console's
+//! channel. Keys arrive as raw bytes and the line is edited here:
+//! backspace, Ctrl-C to drop the line, Ctrl-U to erase it and Ctrl-L to
+//! clear the screen. Cursor keys and other escape sequences are ignored.
+//!
+//! # Commands
+//!
+//! | Command | Description |
+//! |---------|-------------|
+//! | `help` | Lists the commands |
+//! | `echo <text>` | Prints its arguments |
+//! | `clear` | Clears the screen |
+//! | `version` | Kernel version and syscall ABI |
+//! | `mem` | Physical memory |
+//! | `cpus` | Online CPUs |
+//! | `uptime` | Time since boot |
+//! | `colors` | Shows the 16 terminal colors |
+//! | `reboot` | Reboots |
+//! | `poweroff` | Powers off |
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ipc::Channel;
+use libsys::{clock, power, system, Handle};
+
+/// How long to sleep when no key is waiting
+const POLL_INTERVAL: u64 = 5_000_000;
+
+/// Longest command line
+const MAX_LINE: usize = 256;
+
+/// Output is sent once this much is buffered
+const OUTPUT_CHUNK: usize = 4096;
+
+const PROMPT: &str = "\x1b[1;32mrustux\x1b[0m> ";
+
+const COMMANDS: [(&str, &str); 10] = [
+    ("help", "list commands"),
+    ("echo", "print arguments"),
+    ("clear", "clear the screen"),
+    ("version", "kernel version"),
+    ("mem", "physical memory"),
+    ("cpus", "online CPUs"),
+    ("uptime", "time since boot"),
+    ("colors", "show the terminal colors"),
+    ("reboot", "reboot the system"),
+    ("poweroff", "power off the system"),
+];
+
+/// Output buffered into channel messages
+struct Output<'a> {
+    channel: &'a Channel,
+    buf: Vec<u8>,
+}
+
+impl Output<'_> {
+    fn flush(&mut self) {
+        if !self.buf.is_empty() {
+            let _ = self.channel.write(&self.buf, &[]);
+            self.buf.clear();
+        }
+    }
+}
+
+impl Write for Output<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.buf.extend_from_slice(s.as_bytes());
+        if self.buf.len() >= OUTPUT_CHUNK {
+            self.flush();
+        }
+        Ok(())
+    }
+}
+
+/// Escape-sequence state of the key input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscState {
+    Normal,
+    Escape,
+    Csi,
+}
+
+struct Shell {
+    root: Handle,
+    line: [u8; MAX_LINE],
+    len: usize,
+    esc: EscState,
+
+    /// The last key was a carriage return, so a following line feed is
+    /// part of the same Enter
+    after_cr: bool,
+}
+
+impl Shell {
+    fn new(root: Handle) -> Self {
+        Self {
+            root,
+            line: [0; MAX_LINE],
+            len: 0,
+            esc: EscState::Normal,
+            after_cr: false,
+        }
+    }
+
+    /// Handle one key
+    fn key(&mut self, c: u8, out: &mut Output) {
+        let after_cr = core::mem::replace(&mut self.after_cr, c == b'\r');
+        match self.esc {
+            EscState::Escape => {
+                self.esc = if c == b'[' { EscState::Csi } else { EscState::Normal };
+                return;
+            }
+            EscState::Csi => {
+                if (0x40..=0x7E).contains(&c) {
+                    self.esc = EscState::Normal;
+                }
+                return;
+            }
+            EscState::Normal => {}
+        }
+
+        match c {
+            0x1B => self.esc = EscState::Escape,
+            b'\n' if after_cr => {}
+            b'\r' | b'\n' => {
+                let _ = writeln!(out);
+                let len = core::mem::take(&mut self.len);
+                // Only printable ASCII is ever added to the line
+                let line = core::str::from_utf8(&self.line[..len]).unwrap_or("");
+                execute(&self.root, line, out);
+                let _ = write!(out, "{}", PROMPT);
+            }
+            0x7F | 0x08 => {
+                if self.len > 0 {
+                    self.len -= 1;
+                    let _ = write!(out, "\x08 \x08");
+                }
+            }
+            // Ctrl-C
+            0x03 => {
+                self.len = 0;
+                let _ = write!(out, "^C\n{}", PROMPT);
+            }
+            // Ctrl-U
+            0x15 => {
+                self.len = 0;
+                let _ = write!(out, "\r\x1b[K{}", PROMPT);
+            }
+            // Ctrl-L
+            0x0C => {
+                let line = core::str::from_utf8(&self.line[..self.len]).unwrap_or("");
+                let _ = write!(out, "\x1b[2J\x1b[H{}{}", PROMPT, line);
+            }
+            0x20..=0x7E if self.len < MAX_LINE => {
+                self.line[self.len] = c;
+                self.len += 1;
+                let _ = write!(out, "{}", c as char);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Run a command line
+fn execute(root: &Handle, line: &str, out: &mut Output) {
+    let mut args = line.split_whitespace();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return,
+    };
+
+    match command {
+        "help" => {
+            for (name, description) in COMMANDS {
+                let _ = writeln!(out, "  {:<10} {}", name, description);
+            }
+        }
+        "echo" => {
+            for (i, arg) in args.enumerate() {
+                let _ = write!(out, "{}{}", if i > 0 { " " } else { "" }, arg);
+            }
+            let _ = writeln!(out);
+        }
+        "clear" => {
+            let _ = write!(out, "\x1b[2J\x1b[H");
+        }
+        "version" => match system::get_version() {
+            Ok(version) => {
+                let _ = writeln!(
+                    out,
+                    "Rustux {} (ABI {}), build {}",
+                    version.kernel_version(),
+                    version.abi_version,
+                    version.build_id()
+                );
+            }
+            Err(e) => {
+                let _ = writeln!(out, "version: {}", e);
+            }
+        },
+        "mem" => match system::phys_mem() {
+            Ok(bytes) => {
+                let _ = writeln!(out, "{} MiB", bytes / (1024 * 1024));
+            }
+            Err(e) => {
+                let _ = writeln!(out, "mem: {}", e);
+            }
+        },
+        "cpus" => match system::num_cpus() {
+            Ok(cpus) => {
+                let _ = writeln!(out, "{}", cpus);
+            }
+            Err(e) => {
+                let _ = writeln!(out, "cpus: {}", e);
+            }
+        },
+        "uptime" => {
+            let secs = clock::monotonic() / 1_000_000_000;
+            let _ = writeln!(out, "up {}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+        }
+        "colors" => {
+            for base in [40, 100] {
+                for color in 0..8 {
+                    let _ = write!(out, "\x1b[{}m  {:>3}  ", base + color, base + color);
+                }
+                let _ = writeln!(out, "\x1b[0m");
+            }
+        }
+        "reboot" | "poweroff" => {
+            let result = if command == "reboot" { power::reboot(root) } else { power::poweroff(root) };
+            if let Err(e) = result {
+                let _ = writeln!(out, "{}: {}", command, e);
+            }
+        }
+        _ => {
+            let _ = writeln!(out, "{}: command not found", command);
+        }
+    }
+}
+
+/// Serve the console on `channel`, running commands with `root`
+pub fn run(channel: &Channel, root: Handle) {
+    let mut out = Output { channel, buf: Vec::new() };
+    let mut shell = Shell::new(root);
+    let _ = write!(out, "Rustux shell. Type 'help' for commands.\n\n{}", PROMPT);
+    out.flush();
+
+    let mut keys = [0u8; 64];
+    let mut handles = Vec::new();
+    loop {
+        match channel.read(&mut keys, &mut handles) {
+            Ok(n) if n > 0 => {
+                for &c in &keys[..n] {
+                    shell.key(c, &mut out);
+                }
+                out.flush();
+            }
+            _ => rt::timer::sleep(POLL_INTERVAL),
+        }
+    }
+}
@@ -0,0 +1,597 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Terminal Emulator
+//!
+//! Keeps a grid of character cells and draws it onto the framebuffer with
+//! the kernel console's 8x8 font, scaled 2x on large displays. Lines that
+//! scroll off the top are kept in a ring of [`SCROLLBACK_LINES`], which
+//! [`Terminal::scroll_view`] pages through.
+//!
+//! Drawing is deferred to [`Terminal::render`], which redraws only the
+//! rows that changed and scrolls by moving framebuffer memory, so a burst
+//! of output costs one pass over the screen.
+//!
+//! # Escape Sequences
+//!
+//! A subset of VT100/ANSI, enough for colored output and line editing:
+//!
+//! - `ESC [ n A`, `B`, `C`, `D` - Cursor up, down, forward, back
+//! - `ESC [ row ; col H` (or `f`) - Cursor position, from 1
+//! - `ESC [ n G` - Cursor column
+//! - `ESC [ n J` - Erase below (0), above (1), the screen (2) or the
+//!   scrollback (3)
+//! - `ESC [ n K` - Erase to the end (0) or start (1) of the line, or all
+//!   of it (2)
+//! - `ESC [ ... m` - Colors 30-37/40-47 and bright 90-97/100-107, 39/49
+//!   for the defaults, bold (1, 22) and reverse video (7, 27)
+//! - `ESC [ ? 25 h` / `l` - Show or hide the cursor
+//!
+//! Other sequences are swallowed. `\n` also returns the cursor to the
+//! first column, so output meant for a serial console needs no `\r`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::font;
+
+/// Lines kept above the screen
+pub const SCROLLBACK_LINES: usize = 1000;
+
+/// Parameters kept per CSI sequence; later ones are ignored
+const MAX_PARAMS: usize = 8;
+
+/// Default foreground (light gray) and background (black), as palette
+/// indices
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+
+/// ANSI colors 0-7 and their bright variants, as 0xRRGGBB
+const PALETTE: [u32; 16] = [
+    0x000000, // black
+    0xE04040, // red
+    0x40C040, // green
+    0xE0C040, // yellow
+    0x4080E0, // blue
+    0xC040C0, // magenta
+    0x40C0C0, // cyan
+    0xC0C0C0, // white
+    0x606060, // bright black
+    0xFF7070, // bright red
+    0x70FF70, // bright green
+    0xFFFF70, // bright yellow
+    0x70A0FF, // bright blue
+    0xFF70FF, // bright magenta
+    0x70FFFF, // bright cyan
+    0xFFFFFF, // bright white
+];
+
+/// The framebuffer drawn to
+pub struct Framebuffer {
+    /// Mapped pixels
+    pub base: *mut u32,
+
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// Pixels per scan line
+    pub stride: u32,
+
+    /// Blue in the low byte (BGRX), rather than red (RGBX)
+    pub bgr: bool,
+}
+
+/// One character cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    ch: u8,
+    fg: u8,
+    bg: u8,
+}
+
+/// Escape-sequence parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Plain text
+    Normal,
+
+    /// Saw ESC
+    Escape,
+
+    /// Inside CSI (`ESC [`)
+    Csi,
+}
+
+/// Terminal emulator over a framebuffer
+pub struct Terminal {
+    fb: Framebuffer,
+
+    /// Glyph scale factor
+    scale: u32,
+
+    /// Text columns
+    cols: usize,
+
+    /// Text rows
+    rows: usize,
+
+    /// Ring of lines, `cols` cells each: the scrollback, then the screen
+    cells: Vec<Cell>,
+
+    /// Lines in the ring
+    capacity: usize,
+
+    /// Ring index of the screen's top line
+    top: usize,
+
+    /// Scrollback lines in use
+    history: usize,
+
+    /// Lines the view is scrolled back, 0 for the live screen
+    view: usize,
+
+    /// Cursor column; `cols` once a line is full, until the next character
+    /// wraps it
+    x: usize,
+
+    /// Cursor row
+    y: usize,
+
+    /// Current colors and attributes
+    fg: u8,
+    bg: u8,
+    bold: bool,
+    reverse: bool,
+
+    cursor_visible: bool,
+
+    state: State,
+    params: [u16; MAX_PARAMS],
+    param_count: usize,
+
+    /// The CSI sequence started with `?`
+    private: bool,
+
+    /// Screen rows to redraw
+    dirty: Vec<bool>,
+
+    /// Rows the screen scrolled since the last render
+    scrolled: usize,
+
+    /// Where the cursor was last drawn
+    drawn_cursor: Option<(usize, usize)>,
+}
+
+unsafe impl Send for Terminal {}
+
+impl Terminal {
+    /// Create a terminal filling a framebuffer
+    ///
+    /// # Safety
+    ///
+    /// `fb.base` must map at least `fb.stride * fb.height` pixels for as
+    /// long as the terminal lives.
+    pub unsafe fn new(fb: Framebuffer) -> Self {
+        // 8x8 glyphs are unreadable on high resolution panels
+        let scale = if fb.width >= 1600 { 2 } else { 1 };
+        let cols = ((fb.width / (font::FONT_WIDTH * scale)) as usize).max(1);
+        let rows = ((fb.height / (font::FONT_HEIGHT * scale)) as usize).max(1);
+        let capacity = rows + SCROLLBACK_LINES;
+        let blank = Cell { ch: b' ', fg: DEFAULT_FG, bg: DEFAULT_BG };
+
+        let term = Self {
+            fb,
+            scale,
+            cols,
+            rows,
+            cells: vec![blank; capacity * cols],
+            capacity,
+            top: 0,
+            history: 0,
+            view: 0,
+            x: 0,
+            y: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            reverse: false,
+            cursor_visible: true,
+            state: State::Normal,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+            private: false,
+            dirty: vec![true; rows],
+            scrolled: 0,
+            drawn_cursor: None,
+        };
+
+        // The text grid may not cover the right and bottom edges
+        term.fill(0, 0, term.fb.width, term.fb.height, PALETTE[DEFAULT_BG as usize]);
+        term
+    }
+
+    /// Text dimensions (columns, rows)
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Write output, interpreting control characters and escape sequences
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.putc(b);
+        }
+    }
+
+    /// Move the view `lines` further back into the scrollback, or forward
+    /// for a negative count
+    pub fn scroll_view(&mut self, lines: isize) {
+        let view = if lines < 0 {
+            self.view.saturating_sub(lines.unsigned_abs())
+        } else {
+            (self.view + lines as usize).min(self.history)
+        };
+        if view != self.view {
+            self.view = view;
+            self.invalidate();
+        }
+    }
+
+    /// Return the view to the live screen
+    pub fn reset_view(&mut self) {
+        self.scroll_view(-(self.view as isize));
+    }
+
+    /// Whether the view is scrolled back
+    pub fn is_scrolled_back(&self) -> bool {
+        self.view > 0
+    }
+
+    /// Bring the framebuffer up to date
+    pub fn render(&mut self) {
+        if self.scrolled > 0 && self.scrolled < self.rows {
+            self.scroll_pixels(self.scrolled);
+        }
+        self.scrolled = 0;
+
+        if let Some((_, y)) = self.drawn_cursor.take() {
+            self.dirty[y] = true;
+        }
+        let cursor = self.cursor_visible && self.view == 0;
+        if cursor {
+            self.dirty[self.y] = true;
+        }
+
+        for row in 0..self.rows {
+            if core::mem::take(&mut self.dirty[row]) {
+                self.draw_row(row);
+            }
+        }
+        if cursor {
+            self.drawn_cursor = Some((self.x.min(self.cols - 1), self.y));
+        }
+    }
+
+    /// ========================================================================
+    /// Cells
+    /// ========================================================================
+
+    /// Index of the first cell of screen row `row`
+    fn line(&self, row: usize) -> usize {
+        (self.top + row) % self.capacity * self.cols
+    }
+
+    /// Index of the first cell of the line the view shows at `row`
+    fn view_line(&self, row: usize) -> usize {
+        (self.top + self.capacity - self.view + row) % self.capacity * self.cols
+    }
+
+    /// A blank cell in the current background
+    fn blank(&self) -> Cell {
+        Cell { ch: b' ', fg: self.fg, bg: self.bg }
+    }
+
+    /// Blank columns `[from, to)` of screen row `row`
+    fn erase(&mut self, row: usize, from: usize, to: usize) {
+        let line = self.line(row);
+        let blank = self.blank();
+        self.cells[line + from..line + to].fill(blank);
+        self.dirty[row] = true;
+    }
+
+    /// Redraw everything on the next render
+    fn invalidate(&mut self) {
+        self.dirty.fill(true);
+        self.scrolled = 0;
+        self.drawn_cursor = None;
+    }
+
+    /// ========================================================================
+    /// Output
+    /// ========================================================================
+
+    fn putc(&mut self, c: u8) {
+        match self.state {
+            State::Escape => {
+                self.state = if c == b'[' { State::Csi } else { State::Normal };
+                self.params = [0; MAX_PARAMS];
+                self.param_count = 0;
+                self.private = false;
+            }
+            State::Csi => self.csi(c),
+            State::Normal => match c {
+                0x1B => self.state = State::Escape,
+                b'\n' => self.newline(),
+                b'\r' => self.x = 0,
+                b'\t' => {
+                    let next = (self.x + 8) & !7;
+                    self.x = next.min(self.cols - 1);
+                }
+                0x08 => self.x = self.x.min(self.cols - 1).saturating_sub(1),
+                0x20..=0x7E => self.print(c),
+                // Bell and other controls
+                _ => {}
+            },
+        }
+    }
+
+    /// Put a printable character at the cursor
+    fn print(&mut self, c: u8) {
+        if self.x >= self.cols {
+            self.newline();
+        }
+
+        let (mut fg, mut bg) = (self.fg, self.bg);
+        if self.bold && fg < 8 {
+            fg += 8;
+        }
+        if self.reverse {
+            core::mem::swap(&mut fg, &mut bg);
+        }
+
+        let index = self.line(self.y) + self.x;
+        self.cells[index] = Cell { ch: c, fg, bg };
+        self.dirty[self.y] = true;
+        self.x += 1;
+    }
+
+    /// Move to the start of the next line, scrolling at the bottom
+    fn newline(&mut self) {
+        self.x = 0;
+        if self.y + 1 < self.rows {
+            self.y += 1;
+        } else {
+            self.scroll_up();
+        }
+    }
+
+    /// Scroll the screen up a line, into the scrollback
+    fn scroll_up(&mut self) {
+        let max_history = self.capacity - self.rows;
+        let full = self.history == max_history;
+        self.top = (self.top + 1) % self.capacity;
+        self.history = (self.history + 1).min(max_history);
+        let bottom = self.rows - 1;
+        self.erase(bottom, 0, self.cols);
+
+        // A scrolled back view stays on the same lines while it can
+        if self.view > 0 {
+            if full && self.view == self.history {
+                self.invalidate();
+            } else {
+                self.view += 1;
+                self.dirty[bottom] = false;
+            }
+            return;
+        }
+
+        self.dirty.rotate_left(1);
+        self.dirty[bottom] = true;
+        self.scrolled += 1;
+        self.drawn_cursor = self.drawn_cursor.and_then(|(x, y)| Some((x, y.checked_sub(1)?)));
+    }
+
+    /// ========================================================================
+    /// Escape Sequences
+    /// ========================================================================
+
+    /// CSI parameter `i`, or `default` if missing or 0
+    fn param(&self, i: usize, default: usize) -> usize {
+        match self.params[i] {
+            0 => default,
+            n => n as usize,
+        }
+    }
+
+    fn csi(&mut self, c: u8) {
+        match c {
+            b'0'..=b'9' => {
+                self.param_count = self.param_count.max(1);
+                if let Some(param) = self.params.get_mut(self.param_count - 1) {
+                    *param = param.saturating_mul(10).saturating_add((c - b'0') as u16);
+                }
+            }
+            b';' => self.param_count = self.param_count.max(1) + 1,
+            b'?' => self.private = true,
+            0x40..=0x7E => {
+                self.state = State::Normal;
+                self.dispatch(c);
+            }
+            // Intermediate bytes
+            _ => {}
+        }
+    }
+
+    /// Run the CSI sequence ending in `c`
+    fn dispatch(&mut self, c: u8) {
+        if self.private {
+            if self.params[0] == 25 && (c == b'h' || c == b'l') {
+                self.cursor_visible = c == b'h';
+            }
+            return;
+        }
+
+        let (cols, rows) = (self.cols, self.rows);
+        let x = self.x.min(cols - 1);
+        match c {
+            b'A' => {
+                self.x = x;
+                self.y = self.y.saturating_sub(self.param(0, 1));
+            }
+            b'B' => {
+                self.x = x;
+                self.y = (self.y + self.param(0, 1)).min(rows - 1);
+            }
+            b'C' => self.x = (x + self.param(0, 1)).min(cols - 1),
+            b'D' => self.x = x.saturating_sub(self.param(0, 1)),
+            b'G' => self.x = self.param(0, 1).min(cols) - 1,
+            b'H' | b'f' => {
+                self.y = self.param(0, 1).min(rows) - 1;
+                self.x = self.param(1, 1).min(cols) - 1;
+            }
+            b'J' => match self.params[0] {
+                0 => {
+                    self.erase(self.y, x, cols);
+                    for row in self.y + 1..rows {
+                        self.erase(row, 0, cols);
+                    }
+                }
+                1 => {
+                    for row in 0..self.y {
+                        self.erase(row, 0, cols);
+                    }
+                    self.erase(self.y, 0, x + 1);
+                }
+                2 => {
+                    for row in 0..rows {
+                        self.erase(row, 0, cols);
+                    }
+                }
+                3 => {
+                    self.history = 0;
+                    self.view = 0;
+                    self.invalidate();
+                }
+                _ => {}
+            },
+            b'K' => match self.params[0] {
+                0 => self.erase(self.y, x, cols),
+                1 => self.erase(self.y, 0, x + 1),
+                2 => self.erase(self.y, 0, cols),
+                _ => {}
+            },
+            b'm' => {
+                for i in 0..self.param_count.clamp(1, MAX_PARAMS) {
+                    self.sgr(self.params[i]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply an SGR (`ESC [ n m`) parameter
+    fn sgr(&mut self, param: u16) {
+        match param {
+            0 => {
+                self.fg = DEFAULT_FG;
+                self.bg = DEFAULT_BG;
+                self.bold = false;
+                self.reverse = false;
+            }
+            1 => self.bold = true,
+            7 => self.reverse = true,
+            22 => self.bold = false,
+            27 => self.reverse = false,
+            30..=37 => self.fg = (param - 30) as u8,
+            39 => self.fg = DEFAULT_FG,
+            40..=47 => self.bg = (param - 40) as u8,
+            49 => self.bg = DEFAULT_BG,
+            90..=97 => self.fg = (param - 90) as u8 + 8,
+            100..=107 => self.bg = (param - 100) as u8 + 8,
+            _ => {}
+        }
+    }
+
+    /// ========================================================================
+    /// Drawing
+    /// ========================================================================
+
+    /// Convert 0xRRGGBB to the framebuffer's pixel format
+    fn pixel(&self, rgb: u32) -> u32 {
+        if self.fb.bgr {
+            // Memory order B,G,R,X == little-endian 0x00RRGGBB
+            rgb
+        } else {
+            ((rgb & 0xFF) << 16) | (rgb & 0xFF00) | ((rgb >> 16) & 0xFF)
+        }
+    }
+
+    /// Fill a pixel rectangle
+    fn fill(&self, x: u32, y: u32, w: u32, h: u32, rgb: u32) {
+        let color = self.pixel(rgb);
+        for row in y..(y + h).min(self.fb.height) {
+            let line = unsafe { self.fb.base.add((row * self.fb.stride) as usize) };
+            for col in x..(x + w).min(self.fb.width) {
+                unsafe { line.add(col as usize).write_volatile(color) };
+            }
+        }
+    }
+
+    /// Draw a glyph at a text cell
+    fn draw_glyph(&self, col: usize, row: usize, ch: u8, fg: u32, bg: u32) {
+        let glyph = font::glyph(ch);
+        let (fg, bg) = (self.pixel(fg), self.pixel(bg));
+        let scale = self.scale;
+        let px = col as u32 * font::FONT_WIDTH * scale;
+        let py = row as u32 * font::FONT_HEIGHT * scale;
+
+        for (gy, bits) in glyph.iter().enumerate() {
+            for sy in 0..scale {
+                let y = py + gy as u32 * scale + sy;
+                let line = unsafe { self.fb.base.add((y * self.fb.stride) as usize) };
+                for gx in 0..font::FONT_WIDTH {
+                    let color = if bits & (1 << gx) != 0 { fg } else { bg };
+                    for sx in 0..scale {
+                        let x = px + gx * scale + sx;
+                        unsafe { line.add(x as usize).write_volatile(color) };
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw the line the view shows at `row`
+    fn draw_row(&self, row: usize) {
+        let line = self.view_line(row);
+        let cursor = match self.cursor_visible && self.view == 0 && row == self.y {
+            true => Some(self.x.min(self.cols - 1)),
+            false => None,
+        };
+
+        for col in 0..self.cols {
+            let cell = self.cells[line + col];
+            let (mut fg, mut bg) = (PALETTE[cell.fg as usize], PALETTE[cell.bg as usize]);
+            if cursor == Some(col) {
+                core::mem::swap(&mut fg, &mut bg);
+            }
+            self.draw_glyph(col, row, cell.ch, fg, bg);
+        }
+    }
+
+    /// Move the text area up by `lines` rows
+    fn scroll_pixels(&self, lines: usize) {
+        let cell_h = (font::FONT_HEIGHT * self.scale) as usize;
+        let stride = self.fb.stride as usize;
+        let shift = lines * cell_h * stride;
+        let total = self.rows * cell_h * stride;
+
+        unsafe {
+            core::ptr::copy(self.fb.base.add(shift), self.fb.base, total - shift);
+        }
+    }
+}
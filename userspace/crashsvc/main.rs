@@ -0,0 +1,352 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Crash Reporter
+//!
+//! Binds the root job's exception channel, so it hears about every fatal
+//! exception that no job nearer the faulting process handles. For each one
+//! it reads the faulting thread's registers and stack, logs them with a
+//! backtrace, and resumes the thread so its process dies as it would have
+//! without a handler.
+//!
+//! The backtrace is in symbolizer markup (`{{{bt:N:0x...}}}`), which an
+//! offline symbolizer turns into function names and lines using the
+//! binary's symbols. It follows the frame pointer chain through the stack
+//! the kernel saved, so frames come only from code built with frame
+//! pointers, and the walk stops at the first frame past the saved stack.
+//!
+//! It also has the kernel write a core dump of the process, and logs it
+//! base64-encoded between `{{{coredump:begin:<pid>:<size>}}}` and
+//! `{{{coredump:end}}}` lines once the thread is resumed, since there is
+//! no filesystem to keep it on. `scripts/coredump.py` pulls dumps out of a
+//! serial log for debugging off-target.
+//!
+//! Usage: `crashsvc [-a] [-n]`
+//!
+//! - `-a` - Dump every readable mapping, not only writable ones
+//! - `-n` - Don't dump
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate ipc;
+extern crate libsys;
+extern crate rt;
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ipc::Channel;
+use libsys::exception::{self, ExceptionReport, GeneralRegs, STACK_SNAPSHOT_SIZE};
+use libsys::*;
+
+/// Most frames in a backtrace
+const MAX_FRAMES: usize = 64;
+
+/// How long to sleep when no exception is waiting
+const POLL_INTERVAL: u64 = 10_000_000;
+
+const USAGE: &str = "usage: crashsvc [-a] [-n]";
+
+/// Core dump bytes per base64 line
+const DUMP_LINE: usize = 57;
+
+/// Core dump bytes read from the VMO at once
+const DUMP_CHUNK: usize = DUMP_LINE * 64;
+
+/// Core dump header size, for its `size` field at byte 32
+const DUMP_HEADER_SIZE: usize = 40;
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Registers
+/// ============================================================================
+
+/// Names of `GeneralRegs::regs`, in DWARF order
+#[cfg(target_arch = "x86_64")]
+const REG_NAMES: &[&str] = &[
+    "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+#[cfg(target_arch = "aarch64")]
+const REG_NAMES: &[&str] = &[
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7",
+    "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15",
+    "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23",
+    "x24", "x25", "x26", "x27", "x28", "x29", "x30",
+];
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const REG_NAMES: &[&str] = &[];
+
+fn print_regs(w: &mut StdoutWriter, regs: &GeneralRegs) {
+    let _ = writeln!(
+        w,
+        "  pc {:#018x}   sp {:#018x}   fp {:#018x}  flags {:#x}",
+        regs.pc, regs.sp, regs.fp, regs.flags
+    );
+    for (row, names) in REG_NAMES.chunks(4).enumerate() {
+        let _ = write!(w, " ");
+        for (i, name) in names.iter().enumerate() {
+            let _ = write!(w, " {:>3} {:#018x}", name, regs.regs[row * 4 + i]);
+        }
+        let _ = writeln!(w);
+    }
+}
+
+/// ============================================================================
+/// Backtrace
+/// ============================================================================
+
+/// Read the `u64` at `addr` from a stack snapshot that starts at `sp`
+fn stack_word(stack: &[u8], sp: u64, addr: u64) -> Option<u64> {
+    let offset = addr.checked_sub(sp)? as usize;
+    let bytes = stack.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Return addresses on the frame pointer chain from `regs.fp`
+///
+/// Each frame record is the caller's frame pointer followed by the return
+/// address, on both x86_64 and aarch64. The chain must move up the stack,
+/// so a corrupt frame pointer can't loop.
+fn walk_frames(regs: &GeneralRegs, stack: &[u8]) -> Vec<u64> {
+    let mut frames = Vec::new();
+    let mut fp = regs.fp;
+    while frames.len() < MAX_FRAMES {
+        let next = stack_word(stack, regs.sp, fp);
+        let ra = stack_word(stack, regs.sp, fp.wrapping_add(8));
+        let (Some(next), Some(ra)) = (next, ra) else {
+            break;
+        };
+        if ra == 0 {
+            break;
+        }
+        frames.push(ra);
+        if next <= fp {
+            break;
+        }
+        fp = next;
+    }
+    frames
+}
+
+fn print_backtrace(w: &mut StdoutWriter, regs: &GeneralRegs, stack: &[u8]) {
+    let _ = writeln!(w, "{{{{{{reset}}}}}}");
+    let _ = writeln!(w, "{{{{{{bt:0:{:#x}:pc}}}}}}", regs.pc);
+    for (i, ra) in walk_frames(regs, stack).iter().enumerate() {
+        let _ = writeln!(w, "{{{{{{bt:{}:{:#x}:ra}}}}}}", i + 1, ra);
+    }
+}
+
+/// ============================================================================
+/// Core Dumps
+/// ============================================================================
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Write `bytes` as one line of base64
+fn print_base64(w: &mut StdoutWriter, bytes: &[u8]) {
+    let mut line = [0u8; DUMP_LINE / 3 * 4];
+    let mut len = 0;
+    for group in bytes.chunks(3) {
+        let n = (group[0] as u32) << 16
+            | (*group.get(1).unwrap_or(&0) as u32) << 8
+            | *group.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            line[len + i] = if i <= group.len() { BASE64[(n >> (18 - 6 * i)) as usize & 0x3f] } else { b'=' };
+        }
+        len += 4;
+    }
+    let _ = writeln!(w, "{}", core::str::from_utf8(&line[..len]).unwrap_or(""));
+}
+
+/// Dump read buffer; too big for the initial stack
+static mut DUMP: [u8; DUMP_CHUNK] = [0; DUMP_CHUNK];
+
+/// Log the core dump in `vmo` of process `pid`
+fn print_coredump(w: &mut StdoutWriter, pid: u64, vmo: &Vmo) -> Result<()> {
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(DUMP) };
+    vmo.read(0, &mut buf[..DUMP_HEADER_SIZE])?;
+    if &buf[..6] != b"RXCORE" {
+        return Err(Error::new(Status::InvalidArgs));
+    }
+    let size = u64::from_le_bytes(buf[32..40].try_into().unwrap());
+
+    let _ = writeln!(w, "{{{{{{coredump:begin:{}:{}}}}}}}", pid, size);
+    let mut offset = 0;
+    while offset < size {
+        let len = (size - offset).min(DUMP_CHUNK as u64) as usize;
+        vmo.read(offset, &mut buf[..len])?;
+        for line in buf[..len].chunks(DUMP_LINE) {
+            print_base64(w, line);
+        }
+        offset += len as u64;
+    }
+    let _ = writeln!(w, "{{{{{{coredump:end}}}}}}");
+    Ok(())
+}
+
+/// ============================================================================
+/// Reports
+/// ============================================================================
+
+/// What to do with each exception, from the command line
+struct Options {
+    /// Whether to write core dumps
+    dump: bool,
+
+    /// `write_coredump` options
+    dump_options: u32,
+}
+
+/// Stack snapshot buffer; too big for the initial stack
+static mut STACK: [u8; STACK_SNAPSHOT_SIZE] = [0; STACK_SNAPSHOT_SIZE];
+
+/// Log one exception and let its thread go
+fn report(w: &mut StdoutWriter, options: &Options, report: &ExceptionReport) {
+    let _ = writeln!(
+        w,
+        "crashsvc: {} in process {} (koid {}) thread {} (koid {})",
+        report.kind_name(),
+        report.pid,
+        report.process_koid,
+        report.tid,
+        report.thread_koid
+    );
+    if report.kind == exception::EXCP_FATAL_PAGE_FAULT {
+        let _ = writeln!(
+            w,
+            "crashsvc: fault address {:#x}, error {:#x}",
+            report.fault_addr, report.err_code
+        );
+    }
+    if report.arch_status != 0 {
+        let _ = writeln!(w, "crashsvc: arch status {:#x}", report.arch_status);
+    }
+
+    let thread = unsafe { Handle::from_raw(report.tid as u32, Rights::READ | Rights::WRITE) };
+    let stack = unsafe { &mut *core::ptr::addr_of_mut!(STACK) };
+
+    // Fall back on the report if the state is gone
+    let regs = exception::read_regs(&thread).unwrap_or(GeneralRegs {
+        pc: report.pc,
+        sp: report.sp,
+        fp: report.fp,
+        ..GeneralRegs::default()
+    });
+    let len = exception::read_stack(&thread, stack).unwrap_or(0);
+
+    print_regs(w, &regs);
+    print_backtrace(w, &regs, &stack[..len]);
+
+    // Dump before resuming, log after; logging is slow
+    let dump = if options.dump {
+        exception::write_coredump(&thread, options.dump_options)
+            .map_err(|e| {
+                let _ = writeln!(w, "crashsvc: core dump of process {} failed: {:?}", report.pid, e);
+            })
+            .ok()
+    } else {
+        None
+    };
+
+    if let Err(e) = exception::resume(&thread) {
+        let _ = writeln!(w, "crashsvc: resume of thread {} failed: {:?}", report.tid, e);
+    }
+
+    if let Some(vmo) = dump {
+        if let Err(e) = print_coredump(w, report.pid, &vmo) {
+            let _ = writeln!(w, "crashsvc: can't read core dump of process {}: {:?}", report.pid, e);
+        }
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+fn parse_options(argc: isize, argv: *const *const u8) -> Option<Options> {
+    let mut options = Options { dump: true, dump_options: 0 };
+    for i in 1..argc {
+        match unsafe { arg(argv, i) }? {
+            "-a" => options.dump_options |= exception::COREDUMP_ALL_MEMORY,
+            "-n" => options.dump = false,
+            _ => return None,
+        }
+    }
+    Some(options)
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+    let Some(options) = parse_options(argc.max(0) as isize, argv) else {
+        let _ = writeln!(writer, "{}", USAGE);
+        return 1;
+    };
+
+    // Handle 0 is the root job
+    let root_job = unsafe { Handle::from_raw(0, Rights::all()) };
+    let channel = match exception::create_channel(&root_job) {
+        Ok(handle) => unsafe { Channel::from_handle(handle) },
+        Err(e) => {
+            let _ = writeln!(writer, "crashsvc: can't bind the root job: {:?}", e);
+            return 1;
+        }
+    };
+    let _ = writeln!(writer, "crashsvc: watching the root job");
+
+    let mut message = [0u8; core::mem::size_of::<ExceptionReport>()];
+    let mut handles = Vec::new();
+    loop {
+        match channel.read(&mut message, &mut handles) {
+            Ok(0) => rt::timer::sleep(POLL_INTERVAL),
+            Ok(len) => match ExceptionReport::from_bytes(&message[..len]) {
+                Some(exception) => report(&mut writer, &options, &exception),
+                None => {
+                    let _ = writeln!(writer, "crashsvc: ignoring {} byte message", len);
+                }
+            },
+            Err(e) if e.status() == Status::WouldBlock => rt::timer::sleep(POLL_INTERVAL),
+            Err(e) => {
+                let _ = writeln!(writer, "crashsvc: read failed: {:?}", e);
+                return 1;
+            }
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
@@ -29,6 +29,12 @@
 extern "C" {
     /// The main function (user-provided)
     fn main(argc: i32, argv: *const *const u8) -> i32;
+
+    /// Stack guard setup (libc-rx)
+    fn __libc_init_stack_guard(random: *const u8);
+
+    /// File descriptor table setup (libc-rx)
+    fn __libc_init_fdio();
 }
 
 /// Process entry point
@@ -51,7 +57,7 @@ pub unsafe extern "C" fn _start(
     let argv = arg_v as *const *const u8;
 
     // Initialize the C runtime
-    crt_init();
+    crt_init(argv);
 
     // Call global constructors (if any)
     // TODO: Implement constructor support via linker script
@@ -67,14 +73,43 @@ pub unsafe extern "C" fn _start(
 }
 
 /// C runtime initialization
-unsafe fn crt_init() {
+///
+/// Must not be built with stack protection: the guard changes while this
+/// function is on the stack.
+unsafe fn crt_init(argv: *const *const u8) {
+    __libc_init_stack_guard(find_at_random(argv));
+
+    // The kernel passes no startup handles yet, so descriptors 0, 1 and 2
+    // are the debug console
+    libsys::startup::init(core::ptr::null(), core::ptr::null(), 0);
+    __libc_init_fdio();
+
     // TODO: Initialize:
     // - Thread-local storage
     // - Global data (BSS, data segments)
-    // - Standard library (stdio, malloc, etc.)
+    // - Standard library (malloc, etc.)
     // - Signal handlers
 }
 
+/// Find the `AT_RANDOM` bytes the kernel placed on the initial stack
+///
+/// `argc` sits just below `argv`. Returns null if there is no `AT_RANDOM`
+/// entry.
+unsafe fn find_at_random(argv: *const *const u8) -> *const u8 {
+    if argv.is_null() {
+        return core::ptr::null();
+    }
+
+    let stack = (argv as *const u8).sub(core::mem::size_of::<usize>());
+    for entry in parse_auxv(stack) {
+        if let AuxvEntry::Random(addr) = entry {
+            return addr as *const u8;
+        }
+    }
+
+    core::ptr::null()
+}
+
 /// Thread local storage initialization
 ///
 /// This is called for each new thread to set up TLS.
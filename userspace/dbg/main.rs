@@ -0,0 +1,326 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! dbg - Debugger Prototype
+//!
+//! Reads and writes another process's memory and sets software
+//! breakpoints in it, through `rx_process_read_memory` and
+//! `rx_process_write_memory`. The kernel only allows these with
+//! `kernel.enable-debugging-syscalls=true`.
+//!
+//! `break` saves the instruction at the address and writes the
+//! architecture's breakpoint instruction over it, printing the saved bytes
+//! for `clear` to put back. With `-w <job>` it binds the job's exception
+//! channel and waits for the process to hit a breakpoint, then prints the
+//! registers, puts the instruction back and resumes the thread. Handlers
+//! can't repair a thread yet, so the process still dies.
+//!
+//! `hwbreak` arms one of a thread's hardware breakpoints instead, through
+//! `rx_thread_write_state`, leaving its code alone. `hwclear` disarms them
+//! all.
+//!
+//! Usage:
+//!
+//! - `dbg read <pid> <addr> [len]` - Hex dump memory (default 64 bytes)
+//! - `dbg write <pid> <addr> <hex>` - Write bytes, given as hex
+//! - `dbg break <pid> <addr> [-w <job>]` - Set a breakpoint
+//! - `dbg clear <pid> <addr> <hex>` - Put back the saved bytes
+//! - `dbg hwbreak <tid> <addr> [-w <job>]` - Set a hardware breakpoint
+//! - `dbg hwclear <tid>` - Clear a thread's hardware breakpoints
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate ipc;
+extern crate libsys;
+extern crate rt;
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ipc::Channel;
+use libsys::exception::{self, DebugRegs, ExceptionReport};
+use libsys::*;
+
+const USAGE: &str = "usage: dbg read <pid> <addr> [len] | write <pid> <addr> <hex> | \
+                     break <pid> <addr> [-w <job>] | clear <pid> <addr> <hex> | \
+                     hwbreak <tid> <addr> [-w <job>] | hwclear <tid>";
+
+/// Most bytes `read` dumps or `write` writes
+const MAX_BYTES: usize = 4096;
+
+/// How long to sleep when no exception is waiting
+const POLL_INTERVAL: u64 = 10_000_000;
+
+/// Breakpoint instruction: `int3`
+#[cfg(target_arch = "x86_64")]
+const BREAK_INSN: &[u8] = &[0xcc];
+
+/// Breakpoint instruction: `brk #0`
+#[cfg(target_arch = "aarch64")]
+const BREAK_INSN: &[u8] = &[0x00, 0x00, 0x20, 0xd4];
+
+/// Breakpoint instruction: `ebreak`
+#[cfg(target_arch = "riscv64")]
+const BREAK_INSN: &[u8] = &[0x73, 0x00, 0x10, 0x00];
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Arguments
+/// ============================================================================
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parse bytes written as hex digits, two per byte
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || s.len() / 2 > MAX_BYTES {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn process(pid: &str) -> Option<Process> {
+    let pid = parse_u64(pid)?;
+    Some(unsafe { Process::from_handle(Handle::from_raw(pid as u32, Rights::READ | Rights::WRITE)) })
+}
+
+fn thread(tid: &str) -> Option<Handle> {
+    let tid = parse_u64(tid)?;
+    Some(unsafe { Handle::from_raw(tid as u32, Rights::READ | Rights::WRITE) })
+}
+
+/// `-w <job>` at argument `i`, if there are arguments left
+fn wait_job(argc: isize, argv: *const *const u8, i: isize) -> Option<Option<u64>> {
+    if argc == i {
+        return Some(None);
+    }
+    if argc != i + 2 || unsafe { arg(argv, i) } != Some("-w") {
+        return None;
+    }
+    Some(Some(parse_u64(unsafe { arg(argv, i + 1) }?)?))
+}
+
+/// ============================================================================
+/// Commands
+/// ============================================================================
+
+fn read(w: &mut StdoutWriter, process: &Process, addr: u64, len: usize) -> Result<()> {
+    let mut buf = alloc::vec![0u8; len.min(MAX_BYTES)];
+    let read = process.read_memory(addr, &mut buf)?;
+    for (i, line) in buf[..read].chunks(16).enumerate() {
+        let _ = write!(w, "{:#014x}:", addr + i as u64 * 16);
+        for b in line {
+            let _ = write!(w, " {:02x}", b);
+        }
+        let _ = writeln!(w);
+    }
+    if read < len {
+        let _ = writeln!(w, "dbg: only {} bytes are mapped", read);
+    }
+    Ok(())
+}
+
+fn write_all(process: &Process, addr: u64, data: &[u8]) -> Result<()> {
+    if process.write_memory(addr, data)? < data.len() {
+        return Err(Error::new(Status::NoMemory));
+    }
+    Ok(())
+}
+
+fn print_hex(w: &mut StdoutWriter, bytes: &[u8]) {
+    for b in bytes {
+        let _ = write!(w, "{:02x}", b);
+    }
+}
+
+/// Bind the exception channel of `job`, if one is given
+fn bind(job: Option<u64>) -> Result<Option<Channel>> {
+    let Some(job) = job else {
+        return Ok(None);
+    };
+    let job = unsafe { Handle::from_raw(job as u32, Rights::all()) };
+    Ok(Some(unsafe { Channel::from_handle(exception::create_channel(&job)?) }))
+}
+
+fn print_hit(w: &mut StdoutWriter, report: &ExceptionReport) {
+    let _ = writeln!(
+        w,
+        "dbg: thread {} hit {:#x}  sp {:#x}  fp {:#x}  ({}, status {:#x})",
+        report.tid,
+        report.pc,
+        report.sp,
+        report.fp,
+        report.kind_name(),
+        report.arch_status
+    );
+}
+
+fn set_breakpoint(w: &mut StdoutWriter, process: &Process, addr: u64, job: Option<u64>) -> Result<()> {
+    // Bind first, so a process that runs into the breakpoint right away
+    // isn't missed
+    let channel = bind(job)?;
+
+    let mut saved = [0u8; BREAK_INSN.len()];
+    if process.read_memory(addr, &mut saved)? < saved.len() {
+        return Err(Error::new(Status::NoMemory));
+    }
+    write_all(process, addr, BREAK_INSN)?;
+    let _ = write!(w, "dbg: breakpoint at {:#x}, saved ", addr);
+    print_hex(w, &saved);
+    let _ = writeln!(w);
+
+    if let Some(channel) = channel {
+        let pid = process.handle().raw() as u64;
+        let report = wait_for(&channel, |report| {
+            report.pid == pid && report.kind == exception::EXCP_SW_BREAKPOINT
+        })?;
+        print_hit(w, &report);
+        write_all(process, addr, &saved)?;
+        let thread = unsafe { Handle::from_raw(report.tid as u32, Rights::READ | Rights::WRITE) };
+        exception::resume(&thread)?;
+    }
+    Ok(())
+}
+
+fn set_hw_breakpoint(w: &mut StdoutWriter, thread: &Handle, addr: u64, job: Option<u64>) -> Result<()> {
+    let channel = bind(job)?;
+
+    let mut regs = exception::read_debug_regs(thread)?;
+    if regs.count == 0 {
+        return Err(Error::new(Status::NotSupported));
+    }
+    let slot = (0..regs.count as usize)
+        .find(|&slot| regs.enabled & (1 << slot) == 0)
+        .ok_or(Error::new(Status::Busy))?;
+    regs.hw_bps[slot] = addr;
+    regs.enabled |= 1 << slot;
+    exception::write_debug_regs(thread, &regs)?;
+    let _ = writeln!(w, "dbg: hardware breakpoint {} at {:#x}", slot, addr);
+
+    if let Some(channel) = channel {
+        let tid = thread.raw() as u64;
+        let report = wait_for(&channel, |report| {
+            report.tid == tid && report.kind == exception::EXCP_HW_BREAKPOINT
+        })?;
+        print_hit(w, &report);
+        exception::resume(thread)?;
+    }
+    Ok(())
+}
+
+/// Wait on `channel` for the exception `wanted` picks
+///
+/// Other exceptions are let go as they come.
+fn wait_for(channel: &Channel, wanted: impl Fn(&ExceptionReport) -> bool) -> Result<ExceptionReport> {
+    let mut message = [0u8; core::mem::size_of::<ExceptionReport>()];
+    let mut handles = Vec::new();
+    loop {
+        let len = match channel.read(&mut message, &mut handles) {
+            Ok(len) => len,
+            Err(e) if e.status() == Status::WouldBlock => 0,
+            Err(e) => return Err(e),
+        };
+        let Some(report) = ExceptionReport::from_bytes(&message[..len]) else {
+            rt::timer::sleep(POLL_INTERVAL);
+            continue;
+        };
+        if wanted(&report) {
+            return Ok(report);
+        }
+        let thread = unsafe { Handle::from_raw(report.tid as u32, Rights::READ | Rights::WRITE) };
+        let _ = exception::resume(&thread);
+    }
+}
+
+fn run(w: &mut StdoutWriter, argc: isize, argv: *const *const u8) -> Option<Result<()>> {
+    let command = unsafe { arg(argv, 1) }?;
+    if command == "hwclear" && argc == 3 {
+        let thread = thread(unsafe { arg(argv, 2) }?)?;
+        return Some(exception::write_debug_regs(&thread, &DebugRegs::default()));
+    }
+    if command == "hwbreak" {
+        let thread = thread(unsafe { arg(argv, 2) }?)?;
+        let addr = parse_u64(unsafe { arg(argv, 3) }?)?;
+        let job = wait_job(argc, argv, 4)?;
+        return Some(set_hw_breakpoint(w, &thread, addr, job));
+    }
+
+    let process = process(unsafe { arg(argv, 2) }?)?;
+    let addr = parse_u64(unsafe { arg(argv, 3) }?)?;
+
+    let result = match (command, argc) {
+        ("read", 4) => read(w, &process, addr, 64),
+        ("read", 5) => read(w, &process, addr, parse_u64(unsafe { arg(argv, 4) }?)? as usize),
+        ("write", 5) | ("clear", 5) => write_all(&process, addr, &parse_hex(unsafe { arg(argv, 4) }?)?),
+        ("break", _) => set_breakpoint(w, &process, addr, wait_job(argc, argv, 4)?),
+        _ => return None,
+    };
+    Some(result)
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    match run(&mut writer, argc.max(0) as isize, argv) {
+        Some(Ok(())) => 0,
+        Some(Err(e)) if e.status() == Status::NotSupported => {
+            let _ = writeln!(writer, "dbg: needs kernel.enable-debugging-syscalls=true");
+            1
+        }
+        Some(Err(e)) => {
+            let _ = writeln!(writer, "dbg: {:?}", e);
+            1
+        }
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
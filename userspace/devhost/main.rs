@@ -0,0 +1,281 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Driver Host
+//!
+//! Enumerates PCI devices through the root resource and offers each one
+//! to the built-in driver table over a lifecycle channel.
+//!
+//! The device manager and the driver host currently live in the same
+//! process: for every matched device a `LocalDevice` writes a `Bind`
+//! request into a fresh channel and the `DriverHost` on the other end
+//! serves it. Every instance a bound device publishes (the device itself
+//! and, for disks, its partitions) is then opened, and the host keeps
+//! polling the drivers so class protocol clients are served.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libipc;
+extern crate libsys;
+extern crate storage;
+extern crate virtio;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use libddk::host::find_driver;
+use libddk::protocol::bus_protocol;
+use libddk::*;
+use libsys::*;
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ============================================================================
+/// Drivers
+/// ============================================================================
+
+/// QEMU `edu` teaching device
+///
+/// Exercises every DDK path: BAR mapping, interrupts and a DMA buffer.
+struct EduDriver {
+    regs: Option<MmioBuffer>,
+    irq: Option<Interrupt>,
+    dma: Option<DmaBuffer>,
+}
+
+impl EduDriver {
+    const VENDOR_ID: u16 = 0x1234;
+    const DEVICE_ID: u16 = 0x11e8;
+
+    const REG_ID: usize = 0x00;
+    const REG_LIVENESS: usize = 0x04;
+    const REG_IRQ_STATUS: usize = 0x24;
+    const REG_IRQ_RAISE: usize = 0x60;
+    const REG_IRQ_ACK: usize = 0x64;
+
+    fn matches(descriptor: &DeviceDescriptor) -> bool {
+        descriptor.protocol == bus_protocol::PCI
+            && descriptor.vendor_id == Self::VENDOR_ID
+            && descriptor.device_id == Self::DEVICE_ID
+    }
+
+    fn create() -> Box<dyn Driver> {
+        Box::new(EduDriver {
+            regs: None,
+            irq: None,
+            dma: None,
+        })
+    }
+}
+
+impl Driver for EduDriver {
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()> {
+        let info = PciDeviceInfo {
+            vendor_id: descriptor.vendor_id,
+            device_id: descriptor.device_id,
+            ..Default::default()
+        };
+        let device = unsafe { PciDevice::from_handle(device, info) };
+
+        let regs = device.map_bar(0)?;
+        let mut writer = StdoutWriter;
+        let _ = writeln!(writer, "edu: id {:#010x}", regs.read32(Self::REG_ID));
+
+        // The liveness register returns the inverse of what was written
+        regs.write32(Self::REG_LIVENESS, 0x1234_5678);
+        if regs.read32(Self::REG_LIVENESS) != !0x1234_5678 {
+            return Err(Error::new(Status::IoError));
+        }
+
+        device.configure_irq()?;
+        let irq = device.map_interrupt(0)?;
+        device.enable_bus_master(true)?;
+
+        regs.write32(Self::REG_IRQ_RAISE, 1);
+        irq.wait()?;
+        let status = regs.read32(Self::REG_IRQ_STATUS);
+        regs.write32(Self::REG_IRQ_ACK, status);
+        irq.ack()?;
+
+        let bti = Bti::create(0)?;
+        let dma = DmaBuffer::create(&bti, 4096)?;
+        let _ = writeln!(writer, "edu: dma buffer at {:#x}", dma.phys());
+
+        self.regs = Some(regs);
+        self.irq = Some(irq);
+        self.dma = Some(dma);
+        Ok(())
+    }
+
+    fn unbind(&mut self) -> Result<()> {
+        if let Some(dma) = self.dma.take() {
+            dma.release()?;
+        }
+        if let Some(irq) = self.irq.take() {
+            irq.destroy()?;
+        }
+        if let Some(regs) = self.regs.take() {
+            regs.close()?;
+        }
+        Ok(())
+    }
+}
+
+/// Drivers built into this host
+static DRIVERS: [DriverEntry; 5] = [
+    DriverEntry {
+        name: "edu",
+        matches: EduDriver::matches,
+        create: EduDriver::create,
+    },
+    virtio::blk::DRIVER,
+    virtio::net::DRIVER,
+    storage::nvme::DRIVER,
+    storage::ahci::DRIVER,
+];
+
+/// ============================================================================
+/// Device Manager
+/// ============================================================================
+
+/// A device bound to a driver
+struct BoundDevice {
+    /// Lifecycle channel and in-process host
+    local: LocalDevice,
+
+    /// Name of the bound driver
+    name: &'static str,
+}
+
+/// Log what `Open` returned for instance `instance` of `dev`
+fn report_instance(writer: &mut StdoutWriter, dev: &BoundDevice, instance: u32, body: &[u8]) {
+    match dev.name {
+        "virtio-blk" | "nvme" | "ahci" => {
+            if let Some(info) = BlockInfo::from_bytes(body) {
+                let _ = writeln!(
+                    writer,
+                    "devhost: {}/{}: {} blocks of {} bytes",
+                    dev.name, instance, info.block_count, info.block_size
+                );
+            }
+        }
+        "virtio-net" => {
+            if let Some(info) = EthernetInfo::from_bytes(body) {
+                let m = info.mac;
+                let _ = writeln!(
+                    writer,
+                    "devhost: {}/{}: mac {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} mtu {}",
+                    dev.name, instance, m[0], m[1], m[2], m[3], m[4], m[5], info.mtu
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(_argc: i32, _argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "devhost: starting");
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "devhost: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    let mut bound = Vec::new();
+    let mut index = 0;
+    while let Ok(device) = PciDevice::get_nth(&root, index) {
+        index += 1;
+        let info = *device.info();
+        let desc = info.descriptor();
+        let name = match find_driver(&DRIVERS, &desc) {
+            Some(entry) => entry.name,
+            None => continue,
+        };
+
+        let _ = writeln!(
+            writer,
+            "devhost: {:02x}:{:02x}.{} {:04x}:{:04x} -> {}",
+            info.bus_id, info.dev_id, info.func_id, info.vendor_id, info.device_id, name
+        );
+        match LocalDevice::bind(&DRIVERS, desc, device.into_handle()) {
+            Ok(local) => bound.push(BoundDevice { local, name }),
+            Err(e) => {
+                let _ = writeln!(writer, "devhost: bind failed: {:?}", e);
+            }
+        }
+    }
+
+    let _ = writeln!(writer, "devhost: {} devices, {} bound", index, bound.len());
+    if bound.is_empty() {
+        return 0;
+    }
+
+    // Open every instance each device publishes; drivers without a class
+    // protocol decline instance 0
+    for dev in bound.iter_mut() {
+        let mut instance = 0;
+        loop {
+            match dev.local.open(instance) {
+                Ok((body, _)) => report_instance(&mut writer, dev, instance, &body),
+                Err(e) if e.status() == Status::NotFound || e.status() == Status::NotSupported => break,
+                Err(e) => {
+                    let _ = writeln!(writer, "devhost: {}/{}: open failed: {:?}", dev.name, instance, e);
+                    break;
+                }
+            }
+            instance += 1;
+        }
+    }
+
+    // There is no blocking wait on FIFOs yet, so poll the drivers and
+    // drop any whose service fails
+    while !bound.is_empty() {
+        let mut progress = false;
+        bound.retain_mut(|dev| match dev.local.host_mut().poll() {
+            Ok(worked) => {
+                progress |= worked;
+                true
+            }
+            Err(e) => {
+                let _ = writeln!(writer, "devhost: {}: poll failed: {:?}", dev.name, e);
+                false
+            }
+        });
+        if !progress {
+            core::hint::spin_loop();
+        }
+    }
+
+    0
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
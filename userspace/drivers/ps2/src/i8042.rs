@@ -0,0 +1,286 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! i8042 Keyboard Controller
+//!
+//! The controller sits at I/O ports 0x60 (data) and 0x64 (status and
+//! commands). Setup follows the usual order: disable both ports, flush,
+//! program the configuration byte, self-test the controller and the
+//! first port, then reset the keyboard. The second (mouse) port stays
+//! disabled.
+//!
+//! The driver polls rather than taking IRQ 1, so the configuration byte
+//! turns the port interrupts off; the controller's translation stays on,
+//! so keys arrive in scan code set 1 whatever set the keyboard uses.
+
+use libddk::input::{input_kind, modifiers, KeyState};
+use libddk::{InputDevice, InputInfo, IoPorts, KeyEvent};
+use libsys::{clock, Error, Handle, Result, Status};
+
+use crate::scancode::Set1Decoder;
+
+/// Data port
+pub const DATA_PORT: u16 = 0x60;
+
+/// Status register (read) and command register (write)
+pub const COMMAND_PORT: u16 = 0x64;
+
+/// Status register bits
+pub mod status {
+    /// A byte waits in the data port
+    pub const OUTPUT_FULL: u8 = 1 << 0;
+
+    /// The controller hasn't taken the last byte written yet
+    pub const INPUT_FULL: u8 = 1 << 1;
+
+    /// The waiting byte came from the second port
+    pub const AUX_DATA: u8 = 1 << 5;
+}
+
+/// Controller commands
+pub mod command {
+    pub const READ_CONFIG: u8 = 0x20;
+    pub const WRITE_CONFIG: u8 = 0x60;
+    pub const DISABLE_AUX: u8 = 0xA7;
+    pub const TEST_CONTROLLER: u8 = 0xAA;
+    pub const TEST_PORT1: u8 = 0xAB;
+    pub const DISABLE_PORT1: u8 = 0xAD;
+    pub const ENABLE_PORT1: u8 = 0xAE;
+}
+
+/// Configuration byte bits
+pub mod config {
+    pub const PORT1_IRQ: u8 = 1 << 0;
+    pub const PORT2_IRQ: u8 = 1 << 1;
+    pub const PORT1_CLOCK_OFF: u8 = 1 << 4;
+    pub const TRANSLATE: u8 = 1 << 6;
+}
+
+/// Keyboard commands and replies
+pub mod keyboard {
+    pub const SET_LEDS: u8 = 0xED;
+    pub const RESET: u8 = 0xFF;
+
+    pub const ACK: u8 = 0xFA;
+    pub const RESEND: u8 = 0xFE;
+
+    /// Sent after a reset when the keyboard passes its self-test
+    pub const SELF_TEST_PASSED: u8 = 0xAA;
+
+    /// LED bits of `SET_LEDS`
+    pub const LED_SCROLL_LOCK: u8 = 1 << 0;
+    pub const LED_NUM_LOCK: u8 = 1 << 1;
+    pub const LED_CAPS_LOCK: u8 = 1 << 2;
+}
+
+/// Reply to `TEST_CONTROLLER` from a working controller
+const CONTROLLER_OK: u8 = 0x55;
+
+/// Reply to `TEST_PORT1` from a working port
+const PORT_OK: u8 = 0x00;
+
+/// How long the controller gets to take or produce a byte
+const TIMEOUT_NS: i64 = 50_000_000;
+
+/// How long a keyboard may take over its self-test
+const RESET_TIMEOUT_NS: i64 = 1_000_000_000;
+
+/// Times a keyboard command is sent again when the keyboard asks
+const RETRIES: usize = 3;
+
+/// Bytes drained from the controller before giving up on it
+const MAX_FLUSH: usize = 32;
+
+/// `modifiers` bits shown on the LEDs
+const LOCKS: u16 = modifiers::CAPS_LOCK | modifiers::NUM_LOCK | modifiers::SCROLL_LOCK;
+
+/// An i8042 with a keyboard on its first port
+pub struct I8042 {
+    data: IoPorts,
+    control: IoPorts,
+    decoder: Set1Decoder,
+    keys: KeyState,
+
+    /// Lock bits the keyboard's LEDs show
+    leds: u16,
+}
+
+impl I8042 {
+    /// Set up the controller and reset the keyboard
+    ///
+    /// `resource` must grant the controller's ports. Fails with
+    /// `NotFound` if there is no controller or no keyboard on it.
+    pub fn init(resource: &Handle) -> Result<Self> {
+        let mut i8042 = Self {
+            data: IoPorts::request(resource, DATA_PORT, 1)?,
+            control: IoPorts::request(resource, COMMAND_PORT, 1)?,
+            decoder: Set1Decoder::new(),
+            keys: KeyState::new(),
+            leds: 0,
+        };
+
+        // Without a controller the status port floats high
+        if i8042.status() == 0xFF {
+            return Err(Error::new(Status::NotFound));
+        }
+
+        i8042.command(command::DISABLE_PORT1)?;
+        i8042.command(command::DISABLE_AUX)?;
+        i8042.flush();
+
+        let cfg = i8042.command_read(command::READ_CONFIG)?;
+        let cfg = (cfg & !(config::PORT1_IRQ | config::PORT2_IRQ | config::PORT1_CLOCK_OFF)) | config::TRANSLATE;
+        i8042.command_write(command::WRITE_CONFIG, cfg)?;
+
+        if i8042.command_read(command::TEST_CONTROLLER)? != CONTROLLER_OK {
+            return Err(Error::new(Status::IoError));
+        }
+        // The self-test resets the configuration on some controllers
+        i8042.command_write(command::WRITE_CONFIG, cfg)?;
+        if i8042.command_read(command::TEST_PORT1)? != PORT_OK {
+            return Err(Error::new(Status::NotFound));
+        }
+        i8042.command(command::ENABLE_PORT1)?;
+
+        i8042.send(keyboard::RESET).map_err(|_| Error::new(Status::NotFound))?;
+        if i8042.read(RESET_TIMEOUT_NS)? != keyboard::SELF_TEST_PASSED {
+            return Err(Error::new(Status::IoError));
+        }
+        i8042.update_leds()?;
+        Ok(i8042)
+    }
+
+    fn status(&self) -> u8 {
+        self.control.read8(0)
+    }
+
+    /// Wait until the controller can take a byte
+    fn wait_write(&self) -> Result<()> {
+        let deadline = clock::monotonic() + TIMEOUT_NS;
+        while self.status() & status::INPUT_FULL != 0 {
+            if clock::monotonic() > deadline {
+                return Err(Error::new(Status::TimedOut));
+            }
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout` nanoseconds for a byte from the controller
+    fn read(&self, timeout: i64) -> Result<u8> {
+        let deadline = clock::monotonic() + timeout;
+        while self.status() & status::OUTPUT_FULL == 0 {
+            if clock::monotonic() > deadline {
+                return Err(Error::new(Status::TimedOut));
+            }
+            core::hint::spin_loop();
+        }
+        Ok(self.data.read8(0))
+    }
+
+    /// Drop whatever the controller has buffered
+    fn flush(&self) {
+        for _ in 0..MAX_FLUSH {
+            if self.status() & status::OUTPUT_FULL == 0 {
+                break;
+            }
+            self.data.read8(0);
+        }
+    }
+
+    fn command(&self, cmd: u8) -> Result<()> {
+        self.wait_write()?;
+        self.control.write8(0, cmd);
+        Ok(())
+    }
+
+    /// Send a controller command that replies with a byte
+    fn command_read(&self, cmd: u8) -> Result<u8> {
+        self.command(cmd)?;
+        self.read(TIMEOUT_NS)
+    }
+
+    /// Send a controller command followed by a data byte
+    fn command_write(&self, cmd: u8, value: u8) -> Result<()> {
+        self.command(cmd)?;
+        self.wait_write()?;
+        self.data.write8(0, value);
+        Ok(())
+    }
+
+    /// Send a byte to the keyboard and wait for it to be acknowledged
+    ///
+    /// Keys typed meanwhile are lost.
+    fn send(&self, byte: u8) -> Result<()> {
+        for _ in 0..RETRIES {
+            self.wait_write()?;
+            self.data.write8(0, byte);
+            loop {
+                match self.read(TIMEOUT_NS)? {
+                    keyboard::ACK => return Ok(()),
+                    keyboard::RESEND => break,
+                    _ => {}
+                }
+            }
+        }
+        Err(Error::new(Status::IoError))
+    }
+
+    /// Show the lock state on the keyboard's LEDs
+    fn update_leds(&mut self) -> Result<()> {
+        let locks = self.keys.modifiers();
+        let mut leds = 0;
+        if locks & modifiers::SCROLL_LOCK != 0 {
+            leds |= keyboard::LED_SCROLL_LOCK;
+        }
+        if locks & modifiers::NUM_LOCK != 0 {
+            leds |= keyboard::LED_NUM_LOCK;
+        }
+        if locks & modifiers::CAPS_LOCK != 0 {
+            leds |= keyboard::LED_CAPS_LOCK;
+        }
+        self.send(keyboard::SET_LEDS)?;
+        self.send(leds)?;
+        self.leds = locks & LOCKS;
+        Ok(())
+    }
+}
+
+impl InputDevice for I8042 {
+    fn info(&self) -> InputInfo {
+        InputInfo {
+            kind: input_kind::KEYBOARD,
+            vendor_id: 0,
+            product_id: 0,
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<KeyEvent>> {
+        loop {
+            let bits = self.status();
+            if bits & status::OUTPUT_FULL == 0 {
+                return Ok(None);
+            }
+            let byte = self.data.read8(0);
+            if bits & status::AUX_DATA != 0 {
+                continue;
+            }
+
+            let Some((code, pressed)) = self.decoder.feed(byte) else {
+                continue;
+            };
+            let event = self.keys.update(code, pressed);
+            let locks = event.modifiers & LOCKS;
+            if locks != self.leds {
+                // A keyboard that misses the update keeps working, only
+                // with stale LEDs
+                let _ = self.update_leds();
+                self.leds = locks;
+            }
+            return Ok(Some(event));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PS/2 Drivers
+//!
+//! Keyboards on the legacy i8042 controller, as QEMU's PC machines and
+//! older hardware have:
+//! - i8042 controller setup and keyboard commands over x86 I/O ports
+//! - Scan code set 1 decoding into HID usages, so PS/2 keys reach
+//!   consumers as DDK input events like USB ones
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::InputDevice;
+//!
+//! fn keys(root: &libsys::Handle) -> libsys::Result<()> {
+//!     let mut keyboard = ps2::I8042::init(root)?;
+//!     while let Some(event) = keyboard.read_event()? {
+//!         let _ = event.code;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+#![no_std]
+
+pub mod i8042;
+pub mod scancode;
+
+// Re-export commonly used types
+pub use i8042::I8042;
+pub use scancode::Set1Decoder;
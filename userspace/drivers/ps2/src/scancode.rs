@@ -0,0 +1,188 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Scan Code Set 1
+//!
+//! With translation on, the i8042 hands over every keyboard's codes in
+//! set 1: one byte per key, bit 7 set on release, and an `E0` prefix for
+//! the keys the original PC keyboard lacked. Pause sends a make-only
+//! sequence starting with `E1`. Print Screen and the cursor block also
+//! send fake shift presses around their codes, which are dropped here.
+
+use libddk::input::key;
+
+/// Prefix of extended keys
+const EXTENDED: u8 = 0xE0;
+
+/// Prefix of the Pause sequence, `E1 1D 45 E1 9D C5`
+const PAUSE: u8 = 0xE1;
+
+/// Released when bit 7 is set
+const BREAK: u8 = 0x80;
+
+/// HID usages of the unprefixed codes, 0 where there is no key
+const BASE: [u8; 0x59] = [
+    0x00, 0x29, 0x1E, 0x1F, 0x20, 0x21, 0x22, 0x23, // 00-07: Esc 1-6
+    0x24, 0x25, 0x26, 0x27, 0x2D, 0x2E, 0x2A, 0x2B, // 08-0F: 7-0 - = Backspace Tab
+    0x14, 0x1A, 0x08, 0x15, 0x17, 0x1C, 0x18, 0x0C, // 10-17: Q W E R T Y U I
+    0x12, 0x13, 0x2F, 0x30, 0x28, 0xE0, 0x04, 0x16, // 18-1F: O P [ ] Enter LCtrl A S
+    0x07, 0x09, 0x0A, 0x0B, 0x0D, 0x0E, 0x0F, 0x33, // 20-27: D F G H J K L ;
+    0x34, 0x35, 0xE1, 0x31, 0x1D, 0x1B, 0x06, 0x19, // 28-2F: ' ` LShift \ Z X C V
+    0x05, 0x11, 0x10, 0x36, 0x37, 0x38, 0xE5, 0x55, // 30-37: B N M , . / RShift KP*
+    0xE2, 0x2C, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, // 38-3F: LAlt Space CapsLock F1-F5
+    0x3F, 0x40, 0x41, 0x42, 0x43, 0x53, 0x47, 0x5F, // 40-47: F6-F10 NumLock ScrollLock KP7
+    0x60, 0x61, 0x56, 0x5C, 0x5D, 0x5E, 0x57, 0x59, // 48-4F: KP8 KP9 KP- KP4 KP5 KP6 KP+ KP1
+    0x5A, 0x5B, 0x62, 0x63, 0x46, 0x00, 0x64, 0x44, // 50-57: KP2 KP3 KP0 KP. SysRq - NonUS\ F11
+    0x45, // 58: F12
+];
+
+/// HID usage of an `E0`-prefixed code
+fn extended(code: u8) -> Option<u16> {
+    Some(match code {
+        0x1C => key::KP_ENTER,
+        0x1D => key::RIGHT_CTRL,
+        0x35 => key::KP_SLASH,
+        0x37 => key::PRINT_SCREEN,
+        0x38 => key::RIGHT_ALT,
+        0x47 => key::HOME,
+        0x48 => key::UP,
+        0x49 => key::PAGE_UP,
+        0x4B => key::LEFT,
+        0x4D => key::RIGHT,
+        0x4F => key::END,
+        0x50 => key::DOWN,
+        0x51 => key::PAGE_DOWN,
+        0x52 => key::INSERT,
+        0x53 => key::DELETE,
+        0x5B => key::LEFT_META,
+        0x5C => key::RIGHT_META,
+        0x5D => key::MENU,
+        // Fake shifts (E0 2A, E0 36) and anything unknown
+        _ => return None,
+    })
+}
+
+/// Decodes a stream of set 1 bytes into key presses and releases
+pub struct Set1Decoder {
+    /// An `E0` prefix came last
+    extended: bool,
+
+    /// Bytes of the Pause sequence still to come
+    pause: u8,
+}
+
+impl Set1Decoder {
+    pub const fn new() -> Self {
+        Self {
+            extended: false,
+            pause: 0,
+        }
+    }
+
+    /// Decode a byte, returning the HID usage of a key and whether it was
+    /// pressed once its code is complete
+    pub fn feed(&mut self, byte: u8) -> Option<(u16, bool)> {
+        if self.pause > 0 {
+            self.pause -= 1;
+            // Pause has no release; report both at the end of the make
+            // half, and nothing for the break half
+            return match (self.pause, byte) {
+                (3, 0x45) => Some((key::PAUSE, true)),
+                (0, 0xC5) => Some((key::PAUSE, false)),
+                _ => None,
+            };
+        }
+
+        match byte {
+            EXTENDED => {
+                self.extended = true;
+                return None;
+            }
+            PAUSE => {
+                self.pause = 5;
+                return None;
+            }
+            _ => {}
+        }
+
+        let pressed = byte & BREAK == 0;
+        let code = byte & !BREAK;
+        let usage = if core::mem::take(&mut self.extended) {
+            extended(code)?
+        } else {
+            match BASE.get(code as usize) {
+                Some(&usage) if usage != 0 => usage as u16,
+                _ => return None,
+            }
+        };
+        Some((usage, pressed))
+    }
+}
+
+impl Default for Set1Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(decoder: &mut Set1Decoder, bytes: &[u8]) -> Option<(u16, bool)> {
+        let mut last = None;
+        for &byte in bytes {
+            last = decoder.feed(byte);
+        }
+        last
+    }
+
+    #[test]
+    fn test_base_keys() {
+        let mut decoder = Set1Decoder::new();
+        assert_eq!(decoder.feed(0x1E), Some((key::A, true)));
+        assert_eq!(decoder.feed(0x9E), Some((key::A, false)));
+        assert_eq!(decoder.feed(0x2C), Some((key::Z, true)));
+        assert_eq!(decoder.feed(0x0B), Some((key::DIGIT_0, true)));
+        assert_eq!(decoder.feed(0x1C), Some((key::ENTER, true)));
+        assert_eq!(decoder.feed(0x2A), Some((key::LEFT_SHIFT, true)));
+        assert_eq!(decoder.feed(0xAA), Some((key::LEFT_SHIFT, false)));
+        assert_eq!(decoder.feed(0x3A), Some((key::CAPS_LOCK, true)));
+        assert_eq!(decoder.feed(0x44), Some((key::F10, true)));
+        assert_eq!(decoder.feed(0x58), Some((key::F12, true)));
+        assert_eq!(decoder.feed(0x4F), Some((key::KP_1, true)));
+        assert_eq!(decoder.feed(0x55), None);
+        assert_eq!(decoder.feed(0x7F), None);
+    }
+
+    #[test]
+    fn test_extended_keys() {
+        let mut decoder = Set1Decoder::new();
+        assert_eq!(decode(&mut decoder, &[0xE0, 0x48]), Some((key::UP, true)));
+        assert_eq!(decode(&mut decoder, &[0xE0, 0xC8]), Some((key::UP, false)));
+        assert_eq!(decode(&mut decoder, &[0xE0, 0x1D]), Some((key::RIGHT_CTRL, true)));
+        assert_eq!(decode(&mut decoder, &[0xE0, 0x1C]), Some((key::KP_ENTER, true)));
+
+        // The prefix only applies to the next code
+        assert_eq!(decoder.feed(0x48), Some((key::KP_8, true)));
+
+        // Print Screen: fake shift, then the key
+        assert_eq!(decoder.feed(0xE0), None);
+        assert_eq!(decoder.feed(0x2A), None);
+        assert_eq!(decode(&mut decoder, &[0xE0, 0x37]), Some((key::PRINT_SCREEN, true)));
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut decoder = Set1Decoder::new();
+        let mut events = [None; 6];
+        for (event, byte) in events.iter_mut().zip([0xE1, 0x1D, 0x45, 0xE1, 0x9D, 0xC5]) {
+            *event = decoder.feed(byte);
+        }
+        assert_eq!(events, [None, None, Some((key::PAUSE, true)), None, None, Some((key::PAUSE, false))]);
+        assert_eq!(decoder.feed(0x01), Some((key::ESCAPE, true)));
+    }
+}
@@ -0,0 +1,650 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! AHCI SATA Driver
+//!
+//! Brings up an AHCI host bus adapter and publishes the ATA disks on its
+//! ports through the DDK block class:
+//!
+//! 1. Claim the HBA from the firmware, reset it and switch it to AHCI
+//!    mode.
+//! 2. For each implemented port, spin the drive up and wait for the
+//!    link, give the port its command list and received FIS area, and
+//!    start it once the drive has sent its signature.
+//! 3. IDENTIFY DEVICE gives the drive's size and features. Drives
+//!    without 48-bit addressing and ATAPI devices are skipped.
+//!
+//! Each port uses one command slot and one bounce buffer, so commands
+//! are issued one at a time. Completion is polled; the HBA's interrupts
+//! stay disabled. Disks are published in port order (see `DiskSet`).
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::sync::atomic::{fence, Ordering};
+
+use libddk::block::{check_range, health_warning};
+use libddk::dma::PAGE_SIZE;
+use libddk::protocol::bus_protocol;
+use libddk::*;
+use libsys::{clock, Error, Handle, Result, Status};
+
+use crate::ata::{self, command, smart, Identify, ATA_BLOCK_SIZE};
+use crate::disks::DiskSet;
+
+/// PCI class of AHCI controllers
+const PCI_CLASS_STORAGE: u8 = 0x01;
+const PCI_SUBCLASS_SATA: u8 = 0x06;
+const PCI_PROG_IF_AHCI: u8 = 0x01;
+
+/// BAR holding the HBA registers (ABAR)
+const ABAR: u32 = 5;
+
+/// Generic host control registers
+mod hba {
+    pub const CAP: usize = 0x00;
+    pub const GHC: usize = 0x04;
+    pub const PI: usize = 0x0C;
+    pub const CAP2: usize = 0x24;
+    pub const BOHC: usize = 0x28;
+
+    /// Registers of port 0; each port has 128 bytes of registers
+    pub const PORTS: usize = 0x100;
+    pub const PORT_STRIDE: usize = 0x80;
+}
+
+mod cap {
+    /// 64-bit DMA addresses are supported
+    pub const S64A: u32 = 1 << 31;
+}
+
+mod cap2 {
+    /// BIOS/OS handoff is supported
+    pub const BOH: u32 = 1 << 0;
+}
+
+mod ghc {
+    pub const HR: u32 = 1 << 0;
+    pub const AE: u32 = 1 << 31;
+}
+
+mod bohc {
+    pub const BOS: u32 = 1 << 0;
+    pub const OOS: u32 = 1 << 1;
+}
+
+/// Port registers
+mod port {
+    pub const CLB: usize = 0x00;
+    pub const FB: usize = 0x08;
+    pub const IS: usize = 0x10;
+    pub const IE: usize = 0x14;
+    pub const CMD: usize = 0x18;
+    pub const TFD: usize = 0x20;
+    pub const SIG: usize = 0x24;
+    pub const SSTS: usize = 0x28;
+    pub const SERR: usize = 0x30;
+    pub const CI: usize = 0x38;
+}
+
+mod cmd {
+    pub const ST: u32 = 1 << 0;
+    pub const SUD: u32 = 1 << 1;
+    pub const POD: u32 = 1 << 2;
+    pub const FRE: u32 = 1 << 4;
+    pub const FR: u32 = 1 << 14;
+    pub const CR: u32 = 1 << 15;
+}
+
+mod tfd {
+    pub const ERR: u32 = 1 << 0;
+    pub const DRQ: u32 = 1 << 3;
+    pub const BSY: u32 = 1 << 7;
+}
+
+/// Task file error interrupt status
+const IS_TFES: u32 = 1 << 30;
+
+/// `SSTS.DET` once a device is present and the link is up
+const DET_PRESENT: u32 = 3;
+
+/// Signature of an ATA drive
+const SIG_ATA: u32 = 0x0000_0101;
+
+/// FIS types
+const FIS_REGISTER_H2D: u8 = 0x27;
+
+/// Length of a Register Host to Device FIS in dwords
+const FIS_DWORDS: usize = 5;
+
+/// Device register value selecting LBA addressing
+const DEVICE_LBA: u8 = 1 << 6;
+
+/// Largest transfer per command
+pub const MAX_TRANSFER: usize = 64 * 1024;
+
+/// Size of the I/O VMO offered to clients
+const IO_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Port memory layout: command list, received FIS area and the slot 0
+/// command table in the first page, data after
+const COMMAND_LIST_OFFSET: usize = 0;
+const RECEIVED_FIS_OFFSET: usize = 1024;
+const COMMAND_TABLE_OFFSET: usize = 2048;
+const DATA_OFFSET: usize = PAGE_SIZE;
+
+/// Offset of the physical region table in a command table
+const PRDT_OFFSET: usize = 0x80;
+
+/// Offset of the last D2H Register FIS in the received FIS area
+const D2H_FIS_OFFSET: usize = 0x40;
+
+/// How long the HBA gets to reset or stop a port, and a drive to answer
+/// a command
+const TIMEOUT_NS: i64 = 1_000_000_000;
+
+/// How long a port gets to bring its link up
+const LINK_TIMEOUT_NS: i64 = 20_000_000;
+
+/// Build a Register Host to Device FIS
+fn register_fis(command: u8, feature: u8, lba: u64, count: u16) -> [u8; 20] {
+    let lba = lba.to_le_bytes();
+    let count = count.to_le_bytes();
+    let mut fis = [0u8; 20];
+    fis[0] = FIS_REGISTER_H2D;
+    // Command, not device control
+    fis[1] = 1 << 7;
+    fis[2] = command;
+    fis[3] = feature;
+    fis[4..7].copy_from_slice(&lba[0..3]);
+    fis[7] = DEVICE_LBA;
+    fis[8..11].copy_from_slice(&lba[3..6]);
+    fis[12..14].copy_from_slice(&count);
+    fis
+}
+
+/// Build a command header for a 5-dword FIS and `prdt_len` regions
+fn command_header(prdt_len: u16, write: bool, table: u64) -> [u8; 32] {
+    let flags = (FIS_DWORDS as u32) | (write as u32) << 6 | (prdt_len as u32) << 16;
+    let mut header = [0u8; 32];
+    header[0..4].copy_from_slice(&flags.to_le_bytes());
+    header[8..16].copy_from_slice(&table.to_le_bytes());
+    header
+}
+
+/// Build a physical region descriptor for `len` bytes (even, at most
+/// 4 MiB) at `addr`
+fn prd_entry(addr: u64, len: usize) -> [u8; 16] {
+    let mut entry = [0u8; 16];
+    entry[0..8].copy_from_slice(&addr.to_le_bytes());
+    entry[12..16].copy_from_slice(&(len as u32 - 1).to_le_bytes());
+    entry
+}
+
+/// Wait until the bits `mask` of register `offset` read `value`
+fn wait_for(regs: &MmioBuffer, offset: usize, mask: u32, value: u32, timeout: i64) -> Result<()> {
+    let deadline = clock::monotonic() + timeout;
+    while regs.read32(offset) & mask != value {
+        if clock::monotonic() > deadline {
+            return Err(Error::new(Status::TimedOut));
+        }
+        core::hint::spin_loop();
+    }
+    Ok(())
+}
+
+/// One HBA port and its command memory
+struct Port {
+    regs: Rc<MmioBuffer>,
+
+    /// Offset of the port's registers
+    base: usize,
+
+    memory: DmaBuffer,
+}
+
+impl Port {
+    fn read(&self, reg: usize) -> u32 {
+        self.regs.read32(self.base + reg)
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        self.regs.write32(self.base + reg, value)
+    }
+
+    fn wait(&self, reg: usize, mask: u32, value: u32, timeout: i64) -> Result<()> {
+        wait_for(&self.regs, self.base + reg, mask, value, timeout)
+    }
+
+    /// Stop command processing and FIS reception
+    fn stop(&self) -> Result<()> {
+        self.write(port::CMD, self.read(port::CMD) & !cmd::ST);
+        self.wait(port::CMD, cmd::CR, 0, TIMEOUT_NS)?;
+        self.write(port::CMD, self.read(port::CMD) & !cmd::FRE);
+        self.wait(port::CMD, cmd::FR, 0, TIMEOUT_NS)
+    }
+
+    /// Receive FISes, then start command processing once the drive is
+    /// idle
+    fn start(&self) -> Result<()> {
+        self.write(port::CMD, self.read(port::CMD) | cmd::FRE);
+        self.wait(port::TFD, tfd::BSY | tfd::DRQ, 0, TIMEOUT_NS)?;
+        self.write(port::CMD, self.read(port::CMD) | cmd::ST);
+        Ok(())
+    }
+
+    /// Restart the port after a failed command
+    fn recover(&self) {
+        // A port that doesn't come back fails the next command too
+        let _ = self.stop();
+        self.write(port::SERR, u32::MAX);
+        self.write(port::IS, u32::MAX);
+        let _ = self.start();
+    }
+
+    /// Run one command in slot 0 with `len` bytes of data in the bounce
+    /// buffer
+    fn issue(&mut self, fis: &[u8; 20], len: usize, write: bool) -> Result<()> {
+        let table = self.memory.phys() + COMMAND_TABLE_OFFSET as u64;
+        let data = self.memory.phys() + DATA_OFFSET as u64;
+        {
+            let memory = self.memory.as_mut_slice();
+            let table_bytes = &mut memory[COMMAND_TABLE_OFFSET..COMMAND_TABLE_OFFSET + PRDT_OFFSET + 16];
+            table_bytes.fill(0);
+            table_bytes[..fis.len()].copy_from_slice(fis);
+            let prdt_len = if len > 0 {
+                table_bytes[PRDT_OFFSET..].copy_from_slice(&prd_entry(data, len));
+                1
+            } else {
+                0
+            };
+            memory[COMMAND_LIST_OFFSET..COMMAND_LIST_OFFSET + 32]
+                .copy_from_slice(&command_header(prdt_len, write, table));
+        }
+        fence(Ordering::SeqCst);
+
+        self.write(port::IS, u32::MAX);
+        self.write(port::CI, 1);
+
+        let deadline = clock::monotonic() + TIMEOUT_NS;
+        loop {
+            if self.read(port::IS) & IS_TFES != 0 {
+                self.recover();
+                return Err(Error::new(Status::IoError));
+            }
+            if self.read(port::CI) & 1 == 0 {
+                break;
+            }
+            if clock::monotonic() > deadline {
+                self.recover();
+                return Err(Error::new(Status::TimedOut));
+            }
+            core::hint::spin_loop();
+        }
+        fence(Ordering::Acquire);
+
+        if self.read(port::TFD) & tfd::ERR != 0 {
+            return Err(Error::new(Status::IoError));
+        }
+        Ok(())
+    }
+
+    /// The bounce buffer
+    fn data(&self) -> &[u8] {
+        &self.memory.as_slice()[DATA_OFFSET..DATA_OFFSET + MAX_TRANSFER]
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.memory.as_mut_slice()[DATA_OFFSET..DATA_OFFSET + MAX_TRANSFER]
+    }
+
+    /// LBA the drive returned in its last D2H Register FIS
+    fn result_lba(&self) -> u64 {
+        let fis = &self.memory.as_slice()[RECEIVED_FIS_OFFSET + D2H_FIS_OFFSET..];
+        u64::from_le_bytes([fis[4], fis[5], fis[6], fis[8], fis[9], fis[10], 0, 0])
+    }
+}
+
+/// An ATA disk on an AHCI port
+pub struct AhciDisk {
+    port: Port,
+    number: u8,
+    identify: Identify,
+    info: BlockInfo,
+}
+
+impl AhciDisk {
+    /// Bring up port `number` and identify the drive on it
+    ///
+    /// Fails with `NotFound` for a port without a drive and
+    /// `NotSupported` for drives the driver can't use.
+    fn start(regs: Rc<MmioBuffer>, number: u8, bti: &Bti, dma64: bool) -> Result<Self> {
+        let memory = DmaBuffer::create(bti, DATA_OFFSET + MAX_TRANSFER)?;
+        if !dma64 && memory.phys() + memory.size() as u64 > 1 << 32 {
+            return Err(Error::new(Status::NoMemory));
+        }
+        let mut port = Port {
+            regs,
+            base: hba::PORTS + number as usize * hba::PORT_STRIDE,
+            memory,
+        };
+
+        port.write(port::CMD, port.read(port::CMD) | cmd::SUD | cmd::POD);
+        port.wait(port::SSTS, 0xF, DET_PRESENT, LINK_TIMEOUT_NS)
+            .map_err(|_| Error::new(Status::NotFound))?;
+
+        port.stop()?;
+        port.memory.as_mut_slice()[..DATA_OFFSET].fill(0);
+        let phys = port.memory.phys();
+        write64(&port.regs, port.base + port::CLB, phys + COMMAND_LIST_OFFSET as u64);
+        write64(&port.regs, port.base + port::FB, phys + RECEIVED_FIS_OFFSET as u64);
+        port.write(port::SERR, u32::MAX);
+        port.write(port::IS, u32::MAX);
+        port.write(port::IE, 0);
+
+        // The drive sends its signature once FIS reception is on, before
+        // it reports idle
+        port.start()?;
+        if port.read(port::SIG) != SIG_ATA {
+            port.stop()?;
+            return Err(Error::new(Status::NotSupported));
+        }
+
+        port.issue(&register_fis(command::IDENTIFY_DEVICE, 0, 0, 0), ATA_BLOCK_SIZE, false)?;
+        let identify = Identify::parse(port.data()).ok_or(Error::new(Status::Internal))?;
+        let block_size = identify.sector_size;
+        if !identify.lba48
+            || block_size < ATA_BLOCK_SIZE as u32
+            || !block_size.is_power_of_two()
+            || block_size as usize > MAX_TRANSFER
+        {
+            port.stop()?;
+            return Err(Error::new(Status::NotSupported));
+        }
+
+        let info = BlockInfo {
+            block_size,
+            max_transfer: MAX_TRANSFER as u32,
+            block_count: identify.sectors,
+        };
+        Ok(Self {
+            port,
+            number,
+            identify,
+            info,
+        })
+    }
+
+    /// Port the disk is attached to
+    pub fn port(&self) -> u8 {
+        self.number
+    }
+
+    /// What the drive reported in IDENTIFY DEVICE
+    pub fn identify(&self) -> &Identify {
+        &self.identify
+    }
+
+    /// Stop the port
+    pub fn stop(&self) -> Result<()> {
+        self.port.stop()
+    }
+
+    fn transfer_fis(&self, command: u8, lba: u64, len: usize) -> [u8; 20] {
+        register_fis(command, 0, lba, (len / self.info.block_size as usize) as u16)
+    }
+}
+
+impl BlockDevice for AhciDisk {
+    fn info(&self) -> BlockInfo {
+        self.info
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(MAX_TRANSFER) {
+            let fis = self.transfer_fis(command::READ_DMA_EXT, lba, chunk.len());
+            self.port.issue(&fis, chunk.len(), false)?;
+            chunk.copy_from_slice(&self.port.data()[..chunk.len()]);
+            lba += (chunk.len() / self.info.block_size as usize) as u64;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let mut lba = lba;
+        for chunk in buf.chunks(MAX_TRANSFER) {
+            self.port.data_mut()[..chunk.len()].copy_from_slice(chunk);
+            let fis = self.transfer_fis(command::WRITE_DMA_EXT, lba, chunk.len());
+            self.port.issue(&fis, chunk.len(), true)?;
+            lba += (chunk.len() / self.info.block_size as usize) as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.identify.flush {
+            return Ok(());
+        }
+        self.port.issue(&register_fis(command::FLUSH_CACHE_EXT, 0, 0, 0), 0, false)
+    }
+
+    /// Identity from IDENTIFY DEVICE, plus SMART attributes and status
+    /// if the drive has SMART enabled
+    fn health(&mut self) -> Result<DeviceHealth> {
+        let mut health = self.identify.health();
+        if !self.identify.smart {
+            return Ok(health);
+        }
+
+        let fis = register_fis(command::SMART, smart::READ_DATA, smart::LBA_SIGNATURE, 1);
+        self.port.issue(&fis, ATA_BLOCK_SIZE, false)?;
+        // A table with a bad checksum leaves just the identity
+        ata::apply_smart(&mut health, &self.port.data()[..ATA_BLOCK_SIZE]);
+
+        let fis = register_fis(command::SMART, smart::RETURN_STATUS, smart::LBA_SIGNATURE, 0);
+        self.port.issue(&fis, 0, false)?;
+        if self.port.result_lba() & 0xFF_FF00 == smart::LBA_THRESHOLD_EXCEEDED {
+            health.warnings |= health_warning::RELIABILITY;
+        }
+        Ok(health)
+    }
+}
+
+/// An AHCI host bus adapter
+pub struct Ahci {
+    regs: Rc<MmioBuffer>,
+    disks: DiskSet<AhciDisk>,
+    _bti: Bti,
+}
+
+impl Ahci {
+    /// Take over the HBA behind `device` and find its disks
+    pub fn new(device: &PciDevice) -> Result<Self> {
+        let regs = Rc::new(device.map_bar(ABAR)?);
+        device.enable_bus_master(true)?;
+
+        if regs.read32(hba::CAP2) & cap2::BOH != 0 {
+            regs.write32(hba::BOHC, regs.read32(hba::BOHC) | bohc::OOS);
+            wait_for(&regs, hba::BOHC, bohc::BOS, 0, TIMEOUT_NS)?;
+        }
+
+        // Reset, then turn AHCI mode back on; interrupts stay disabled
+        regs.write32(hba::GHC, ghc::AE);
+        regs.write32(hba::GHC, ghc::AE | ghc::HR);
+        wait_for(&regs, hba::GHC, ghc::HR, 0, TIMEOUT_NS)?;
+        regs.write32(hba::GHC, ghc::AE);
+
+        let dma64 = regs.read32(hba::CAP) & cap::S64A != 0;
+        let implemented = regs.read32(hba::PI);
+        let bti = Bti::create(0)?;
+        let mut disks = DiskSet::new();
+        for number in 0..32u8 {
+            if implemented & (1 << number) == 0 {
+                continue;
+            }
+            // Empty ports and drives the driver can't use are skipped
+            if let Ok(disk) = AhciDisk::start(regs.clone(), number, &bti, dma64) {
+                disks.push(disk);
+            }
+        }
+
+        Ok(Self {
+            regs,
+            disks,
+            _bti: bti,
+        })
+    }
+
+    /// The disks found, in port order
+    pub fn disks(&self) -> impl Iterator<Item = &AhciDisk> {
+        self.disks.devices()
+    }
+
+    /// Stop every port
+    pub fn stop(self) -> Result<()> {
+        for disk in self.disks.into_devices() {
+            disk.stop()?;
+        }
+        self.regs.write32(hba::GHC, ghc::AE);
+        Ok(())
+    }
+
+    /// Connect a client to `instance`
+    pub fn open(&mut self, instance: u32) -> Result<Connection> {
+        self.disks.open(instance, IO_BUFFER_SIZE)
+    }
+
+    /// Serve every open instance
+    pub fn poll(&mut self) -> Result<bool> {
+        self.disks.poll()
+    }
+}
+
+/// Write a 64-bit register as two 32-bit halves, low first
+fn write64(regs: &MmioBuffer, offset: usize, value: u64) {
+    regs.write32(offset, value as u32);
+    regs.write32(offset + 4, (value >> 32) as u32);
+}
+
+/// ============================================================================
+/// Driver
+/// ============================================================================
+
+/// AHCI driver for the driver host
+pub struct AhciDriver {
+    device: Option<PciDevice>,
+    controller: Option<Ahci>,
+}
+
+impl AhciDriver {
+    fn matches(descriptor: &DeviceDescriptor) -> bool {
+        descriptor.protocol == bus_protocol::PCI
+            && descriptor.base_class == PCI_CLASS_STORAGE
+            && descriptor.sub_class == PCI_SUBCLASS_SATA
+            && descriptor.prog_if == PCI_PROG_IF_AHCI
+    }
+
+    fn create() -> Box<dyn Driver> {
+        Box::new(AhciDriver {
+            device: None,
+            controller: None,
+        })
+    }
+}
+
+impl Driver for AhciDriver {
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()> {
+        let info = PciDeviceInfo {
+            vendor_id: descriptor.vendor_id,
+            device_id: descriptor.device_id,
+            base_class: descriptor.base_class,
+            sub_class: descriptor.sub_class,
+            prog_if: descriptor.prog_if,
+            ..Default::default()
+        };
+        let device = unsafe { PciDevice::from_handle(device, info) };
+        self.controller = Some(Ahci::new(&device)?);
+        self.device = Some(device);
+        Ok(())
+    }
+
+    fn unbind(&mut self) -> Result<()> {
+        if let Some(controller) = self.controller.take() {
+            controller.stop()?;
+        }
+        if let Some(device) = self.device.take() {
+            device.enable_bus_master(false)?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, instance: u32) -> Result<Connection> {
+        self.controller.as_mut().ok_or(Error::new(Status::BadState))?.open(instance)
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        match self.controller.as_mut() {
+            Some(controller) => controller.poll(),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Driver table entry
+pub const DRIVER: DriverEntry = DriverEntry {
+    name: "ahci",
+    matches: AhciDriver::matches,
+    create: AhciDriver::create,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let mut descriptor = DeviceDescriptor {
+            protocol: bus_protocol::PCI,
+            vendor_id: 0x8086,
+            device_id: 0x2922,
+            base_class: 0x01,
+            sub_class: 0x06,
+            prog_if: 0x01,
+            revision_id: 2,
+        };
+        assert!(AhciDriver::matches(&descriptor));
+        // NVMe
+        descriptor.sub_class = 0x08;
+        descriptor.prog_if = 0x02;
+        assert!(!AhciDriver::matches(&descriptor));
+    }
+
+    #[test]
+    fn test_register_fis() {
+        let fis = register_fis(command::READ_DMA_EXT, 0, 0x0605_0403_0201, 0x0180);
+        assert_eq!(&fis[0..4], &[0x27, 0x80, 0x25, 0x00]);
+        assert_eq!(&fis[4..8], &[0x01, 0x02, 0x03, DEVICE_LBA]);
+        assert_eq!(&fis[8..12], &[0x04, 0x05, 0x06, 0x00]);
+        assert_eq!(&fis[12..14], &[0x80, 0x01]);
+
+        let fis = register_fis(command::SMART, smart::RETURN_STATUS, smart::LBA_SIGNATURE, 0);
+        assert_eq!(&fis[2..7], &[0xB0, 0xDA, 0x00, 0x4F, 0xC2]);
+    }
+
+    #[test]
+    fn test_command_structures() {
+        let header = command_header(1, true, 0x1_2345_6780);
+        assert_eq!(u32::from_le_bytes(header[0..4].try_into().unwrap()), 5 | 1 << 6 | 1 << 16);
+        assert_eq!(u64::from_le_bytes(header[8..16].try_into().unwrap()), 0x1_2345_6780);
+        assert_eq!(&header[4..8], &[0; 4]);
+
+        let entry = prd_entry(0xdead_b000, 512);
+        assert_eq!(u64::from_le_bytes(entry[0..8].try_into().unwrap()), 0xdead_b000);
+        assert_eq!(u32::from_le_bytes(entry[12..16].try_into().unwrap()), 511);
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ATA Commands and Data Structures
+//!
+//! The parts of ACS the AHCI driver needs: command codes, the IDENTIFY
+//! DEVICE data and the SMART attribute table. Both are 512-byte blocks
+//! of little-endian 16-bit words.
+
+use libddk::DeviceHealth;
+
+/// Size of IDENTIFY DEVICE and SMART READ DATA blocks
+pub const ATA_BLOCK_SIZE: usize = 512;
+
+/// Command codes
+pub mod command {
+    pub const READ_DMA_EXT: u8 = 0x25;
+    pub const WRITE_DMA_EXT: u8 = 0x35;
+    pub const SMART: u8 = 0xB0;
+    pub const FLUSH_CACHE_EXT: u8 = 0xEA;
+    pub const IDENTIFY_DEVICE: u8 = 0xEC;
+}
+
+/// SMART subcommands, passed in the feature register
+pub mod smart {
+    pub const READ_DATA: u8 = 0xD0;
+    pub const RETURN_STATUS: u8 = 0xDA;
+
+    /// LBA bits 8-23 every SMART command carries
+    pub const LBA_SIGNATURE: u64 = 0xC2_4F << 8;
+
+    /// LBA bits 8-23 after RETURN STATUS when an attribute is past its
+    /// threshold
+    pub const LBA_THRESHOLD_EXCEEDED: u64 = 0x2C_F4 << 8;
+}
+
+/// SMART attribute IDs the driver reports
+pub mod attribute {
+    pub const REALLOCATED_SECTORS: u8 = 5;
+    pub const POWER_ON_HOURS: u8 = 9;
+    pub const POWER_CYCLES: u8 = 12;
+    pub const UNEXPECTED_POWER_LOSS: u8 = 174;
+    pub const REPORTED_UNCORRECTABLE: u8 = 187;
+    pub const AIRFLOW_TEMPERATURE: u8 = 190;
+    pub const POWER_OFF_RETRACTS: u8 = 192;
+    pub const TEMPERATURE: u8 = 194;
+    pub const OFFLINE_UNCORRECTABLE: u8 = 198;
+}
+
+fn word(data: &[u8], index: usize) -> u16 {
+    u16::from_le_bytes([data[index * 2], data[index * 2 + 1]])
+}
+
+/// Whether a command set word holds valid bits (bit 14 set, 15 clear)
+fn word_valid(value: u16) -> bool {
+    value & 0xC000 == 0x4000
+}
+
+/// Copy an ATA string, which keeps two characters per word with the
+/// first in the high byte
+fn copy_string(dest: &mut [u8], data: &[u8], first_word: usize) {
+    for (i, pair) in dest.chunks_exact_mut(2).enumerate() {
+        let [high, low] = word(data, first_word + i).to_be_bytes();
+        pair[0] = high;
+        pair[1] = low;
+    }
+}
+
+/// What IDENTIFY DEVICE says about a drive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identify {
+    pub model: [u8; 40],
+    pub serial: [u8; 20],
+    pub firmware: [u8; 8],
+
+    /// Addressable logical sectors
+    pub sectors: u64,
+
+    /// Bytes per logical sector
+    pub sector_size: u32,
+
+    /// 48-bit LBA commands are supported
+    pub lba48: bool,
+
+    /// FLUSH CACHE EXT is supported
+    pub flush: bool,
+
+    /// The SMART feature set is supported and enabled
+    pub smart: bool,
+}
+
+impl Identify {
+    /// Parse an IDENTIFY DEVICE block
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < ATA_BLOCK_SIZE {
+            return None;
+        }
+
+        let mut identify = Self {
+            model: [b' '; 40],
+            serial: [b' '; 20],
+            firmware: [b' '; 8],
+            sectors: 0,
+            sector_size: ATA_BLOCK_SIZE as u32,
+            lba48: false,
+            flush: false,
+            smart: false,
+        };
+        copy_string(&mut identify.serial, data, 10);
+        copy_string(&mut identify.firmware, data, 23);
+        copy_string(&mut identify.model, data, 27);
+
+        let supported = word(data, 82);
+        let supported2 = word(data, 83);
+        let enabled = word(data, 85);
+        if word_valid(supported2) {
+            identify.lba48 = supported2 & (1 << 10) != 0;
+            identify.flush = supported2 & (1 << 13) != 0;
+        }
+        identify.smart = supported & 1 != 0 && enabled & 1 != 0;
+
+        identify.sectors = if identify.lba48 {
+            (0..4).fold(0u64, |acc, i| acc | (word(data, 100 + i) as u64) << (16 * i))
+        } else {
+            word(data, 60) as u64 | (word(data, 61) as u64) << 16
+        };
+
+        // Logical sectors longer than 256 words give their size in words
+        let sector_info = word(data, 106);
+        if word_valid(sector_info) && sector_info & (1 << 12) != 0 {
+            let words = word(data, 117) as u32 | (word(data, 118) as u32) << 16;
+            identify.sector_size = words * 2;
+        }
+        Some(identify)
+    }
+
+    /// Start a health record with the drive's identity
+    pub fn health(&self) -> DeviceHealth {
+        let mut health = DeviceHealth::new();
+        health.model = self.model;
+        health.serial = self.serial;
+        health.firmware = self.firmware;
+        health
+    }
+}
+
+/// One entry of the SMART attribute table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmartAttribute {
+    pub id: u8,
+
+    /// Normalized value, compared against the threshold by the drive
+    pub current: u8,
+    pub worst: u8,
+
+    /// Vendor-specific raw value (48 bits)
+    pub raw: u64,
+}
+
+/// Attributes of a SMART READ DATA block
+///
+/// Returns `None` if the block's checksum is wrong.
+pub fn smart_attributes(data: &[u8]) -> Option<impl Iterator<Item = SmartAttribute> + '_> {
+    let data = data.get(..ATA_BLOCK_SIZE)?;
+    if data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+        return None;
+    }
+    // 30 entries of 12 bytes after the 2-byte revision; ID 0 is unused
+    let table = &data[2..2 + 30 * 12];
+    Some(table.chunks_exact(12).filter(|entry| entry[0] != 0).map(|entry| {
+        let mut raw = [0u8; 8];
+        raw[..6].copy_from_slice(&entry[5..11]);
+        SmartAttribute {
+            id: entry[0],
+            current: entry[3],
+            worst: entry[4],
+            raw: u64::from_le_bytes(raw),
+        }
+    }))
+}
+
+/// Fill `health` from a SMART READ DATA block
+///
+/// Raw values are vendor-specific; the attributes used here are the ones
+/// drives agree on, and only their low bytes are trusted where vendors
+/// pack extra data above.
+pub fn apply_smart(health: &mut DeviceHealth, data: &[u8]) -> bool {
+    let attributes = match smart_attributes(data) {
+        Some(attributes) => attributes,
+        None => return false,
+    };
+
+    for attr in attributes {
+        match attr.id {
+            attribute::REALLOCATED_SECTORS => health.reallocated_sectors = Some(attr.raw & 0xFFFF_FFFF),
+            attribute::POWER_ON_HOURS => health.power_on_hours = Some(attr.raw & 0xFFFF_FFFF),
+            attribute::POWER_CYCLES => health.power_cycles = Some(attr.raw & 0xFFFF_FFFF),
+            attribute::UNEXPECTED_POWER_LOSS | attribute::POWER_OFF_RETRACTS => {
+                health.unsafe_shutdowns = Some(attr.raw & 0xFFFF_FFFF)
+            }
+            attribute::REPORTED_UNCORRECTABLE | attribute::OFFLINE_UNCORRECTABLE => {
+                let errors = health.media_errors.unwrap_or(0).max(attr.raw & 0xFFFF_FFFF);
+                health.media_errors = Some(errors);
+            }
+            attribute::TEMPERATURE => health.temperature = Some(attr.raw as u8 as i8 as i16),
+            attribute::AIRFLOW_TEMPERATURE if health.temperature.is_none() => {
+                health.temperature = Some(attr.raw as u8 as i8 as i16)
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_word(data: &mut [u8], index: usize, value: u16) {
+        data[index * 2..index * 2 + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_string(data: &mut [u8], first_word: usize, text: &[u8]) {
+        for (i, pair) in text.chunks(2).enumerate() {
+            let low = pair.get(1).copied().unwrap_or(b' ');
+            set_word(data, first_word + i, u16::from_be_bytes([pair[0], low]));
+        }
+    }
+
+    fn set_checksum(data: &mut [u8]) {
+        let sum = data[..511].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        data[511] = sum.wrapping_neg();
+    }
+
+    #[test]
+    fn test_identify() {
+        let mut data = [0u8; 512];
+        set_string(&mut data, 10, b"QM00001");
+        set_string(&mut data, 23, b"2.5+");
+        set_string(&mut data, 27, b"QEMU HARDDISK");
+        set_word(&mut data, 60, 0xFFFF);
+        set_word(&mut data, 61, 0x0FFF);
+        set_word(&mut data, 82, 1);
+        set_word(&mut data, 83, 0x4000 | 1 << 10 | 1 << 13);
+        set_word(&mut data, 85, 1);
+        set_word(&mut data, 100, 0x0000);
+        set_word(&mut data, 101, 0x0400);
+
+        let identify = Identify::parse(&data).unwrap();
+        let health = identify.health();
+        assert_eq!(health.model_name(), "QEMU HARDDISK");
+        assert_eq!(health.serial_number(), "QM00001");
+        assert_eq!(health.firmware_revision(), "2.5+");
+        assert!(identify.lba48 && identify.flush && identify.smart);
+        assert_eq!(identify.sectors, 0x0400_0000);
+        assert_eq!(identify.sector_size, 512);
+
+        // Without LBA48 the 28-bit count applies; bits without the
+        // validity pattern are ignored
+        set_word(&mut data, 83, 1 << 10);
+        set_word(&mut data, 106, 0x4000 | 1 << 12);
+        set_word(&mut data, 117, 2048);
+        let identify = Identify::parse(&data).unwrap();
+        assert!(!identify.lba48 && !identify.flush);
+        assert_eq!(identify.sectors, 0x0FFF_FFFF);
+        assert_eq!(identify.sector_size, 4096);
+
+        assert_eq!(Identify::parse(&data[..100]), None);
+    }
+
+    #[test]
+    fn test_smart_attributes() {
+        let mut data = [0u8; 512];
+        let entries: [(u8, u64); 5] = [
+            (attribute::POWER_ON_HOURS, 0xAB_0000_1234),
+            (attribute::POWER_CYCLES, 42),
+            (attribute::TEMPERATURE, 0x0032_0014_0025),
+            (attribute::OFFLINE_UNCORRECTABLE, 3),
+            (attribute::REPORTED_UNCORRECTABLE, 1),
+        ];
+        for (i, &(id, raw)) in entries.iter().enumerate() {
+            let entry = &mut data[2 + i * 12..2 + (i + 1) * 12];
+            entry[0] = id;
+            entry[3] = 100;
+            entry[4] = 99;
+            entry[5..11].copy_from_slice(&raw.to_le_bytes()[..6]);
+        }
+
+        set_checksum(&mut data);
+        assert_eq!(smart_attributes(&data).unwrap().count(), 5);
+
+        let mut health = DeviceHealth::new();
+        assert!(apply_smart(&mut health, &data));
+        assert_eq!(health.power_on_hours, Some(0x1234));
+        assert_eq!(health.power_cycles, Some(42));
+        assert_eq!(health.temperature, Some(0x25));
+        assert_eq!(health.media_errors, Some(3));
+        assert_eq!(health.reallocated_sectors, None);
+        assert!(health.healthy());
+
+        data[100] ^= 1;
+        assert!(smart_attributes(&data).is_none());
+        assert!(!apply_smart(&mut health, &data));
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Disk Sets
+//!
+//! A storage controller can have several disks (SATA ports, NVMe
+//! namespaces), each with its own `BlockStack`. `DiskSet` numbers their
+//! instances one after the other: the first disk's whole-device instance
+//! is 0 and its partitions follow, then the second disk's whole-device
+//! instance, and so on. A controller with one disk numbers its
+//! instances exactly like a `BlockStack`.
+
+use alloc::vec::Vec;
+
+use libddk::{BlockDevice, BlockInfo, BlockStack, Connection};
+use libsys::{Error, Result, Status};
+
+/// The disks of one controller
+pub struct DiskSet<D: BlockDevice> {
+    stacks: Vec<BlockStack<D>>,
+}
+
+impl<D: BlockDevice> DiskSet<D> {
+    /// An empty set
+    pub fn new() -> Self {
+        Self { stacks: Vec::new() }
+    }
+
+    /// Add a disk, scanning it for partitions
+    pub fn push(&mut self, device: D) {
+        self.stacks.push(BlockStack::new(device));
+    }
+
+    /// Number of disks
+    pub fn len(&self) -> usize {
+        self.stacks.len()
+    }
+
+    /// Whether the controller has no disks
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    /// The disks, in the order they were added
+    pub fn devices(&self) -> impl Iterator<Item = &D> {
+        self.stacks.iter().map(BlockStack::device)
+    }
+
+    /// Disk index and instance within that disk's stack for `instance`
+    fn locate(&self, instance: u32) -> Result<(usize, u32)> {
+        let mut first = 0;
+        for (index, stack) in self.stacks.iter().enumerate() {
+            let count = stack.instance_count();
+            if instance < first + count {
+                return Ok((index, instance - first));
+            }
+            first += count;
+        }
+        Err(Error::new(Status::NotFound))
+    }
+
+    /// Geometry of `instance`
+    pub fn instance_info(&self, instance: u32) -> Result<BlockInfo> {
+        let (index, local) = self.locate(instance)?;
+        self.stacks[index].instance_info(local)
+    }
+
+    /// Connect a client to `instance` with an I/O VMO of `io_size` bytes
+    pub fn open(&mut self, instance: u32, io_size: usize) -> Result<Connection> {
+        let (index, local) = self.locate(instance)?;
+        self.stacks[index].open(local, io_size)
+    }
+
+    /// Serve every open instance of every disk
+    ///
+    /// Returns true if any request was processed.
+    pub fn poll(&mut self) -> Result<bool> {
+        let mut progress = false;
+        for stack in self.stacks.iter_mut() {
+            progress |= stack.poll()?;
+        }
+        Ok(progress)
+    }
+
+    /// Close all instances and return the disks
+    pub fn into_devices(self) -> Vec<D> {
+        self.stacks.into_iter().map(BlockStack::into_inner).collect()
+    }
+}
+
+impl<D: BlockDevice> Default for DiskSet<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    struct RamDisk(Vec<u8>);
+
+    impl BlockDevice for RamDisk {
+        fn info(&self) -> BlockInfo {
+            BlockInfo {
+                block_size: 512,
+                max_transfer: 0,
+                block_count: (self.0.len() / 512) as u64,
+            }
+        }
+
+        fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+            let start = lba as usize * 512;
+            buf.copy_from_slice(&self.0[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+            let start = lba as usize * 512;
+            self.0[start..start + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_instance_numbering() {
+        let mut disks = DiskSet::new();
+        assert!(disks.is_empty());
+        assert_eq!(disks.instance_info(0).unwrap_err().status(), Status::NotFound);
+
+        disks.push(RamDisk(vec![0u8; 16 * 512]));
+        disks.push(RamDisk(vec![0u8; 32 * 512]));
+        assert_eq!(disks.len(), 2);
+
+        // Neither disk has a GPT, so each publishes only itself
+        assert_eq!(disks.instance_info(0).unwrap().block_count, 16);
+        assert_eq!(disks.instance_info(1).unwrap().block_count, 32);
+        assert_eq!(disks.instance_info(2).unwrap_err().status(), Status::NotFound);
+        assert_eq!(disks.into_devices().len(), 2);
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Storage Drivers
+//!
+//! Drivers for the disks an installation targets on real hardware:
+//! - NVMe: admin and I/O queue pairs with MSI-X, controller and
+//!   namespace identification, the SMART / Health log
+//! - AHCI: SATA ports, IDENTIFY DEVICE and SMART for ATA disks
+//!
+//! Both publish their disks through the DDK block class, with GPT
+//! partitions as further instances, and answer health requests with
+//! what the drive reports about itself.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::DriverEntry;
+//!
+//! static DRIVERS: [DriverEntry; 2] = [storage::nvme::DRIVER, storage::ahci::DRIVER];
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod ahci;
+pub mod ata;
+pub mod disks;
+pub mod nvme;
+
+// Re-export commonly used types
+pub use ahci::{Ahci, AhciDisk};
+pub use ata::Identify;
+pub use disks::DiskSet;
+pub use nvme::{Namespace, Nvme};
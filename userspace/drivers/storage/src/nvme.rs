@@ -0,0 +1,936 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! NVMe Driver
+//!
+//! Brings up an NVMe controller and publishes its namespaces through the
+//! DDK block class:
+//!
+//! 1. Disable the controller, hand it the admin queue pair and enable it
+//!    again.
+//! 2. Identify the controller, ask for one I/O queue pair and create it.
+//! 3. Identify each active namespace. Namespaces are published in the
+//!    order the controller lists them (see `DiskSet`).
+//!
+//! Both queue pairs get their own MSI-X vector if the device has two,
+//! and share one otherwise; without any interrupt mode the driver polls.
+//! The kernel has no blocking interrupt wait yet, so completions are
+//! always found through the phase bit and vectors are acknowledged as
+//! deliveries come in to keep them armed. Commands go through one bounce
+//! buffer and are issued one at a time.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ptr;
+use core::sync::atomic::{fence, Ordering};
+
+use libddk::block::check_range;
+use libddk::dma::PAGE_SIZE;
+use libddk::pci::irq_mode;
+use libddk::protocol::bus_protocol;
+use libddk::*;
+use libsys::{clock, Error, Handle, Result, Status};
+
+use crate::disks::DiskSet;
+
+/// PCI class of NVMe controllers
+const PCI_CLASS_STORAGE: u8 = 0x01;
+const PCI_SUBCLASS_NVM: u8 = 0x08;
+const PCI_PROG_IF_NVME: u8 = 0x02;
+
+/// Controller registers
+mod reg {
+    pub const CAP: usize = 0x00;
+    pub const CC: usize = 0x14;
+    pub const CSTS: usize = 0x1C;
+    pub const AQA: usize = 0x24;
+    pub const ASQ: usize = 0x28;
+    pub const ACQ: usize = 0x30;
+
+    /// First doorbell
+    pub const DOORBELLS: usize = 0x1000;
+}
+
+mod cc {
+    pub const EN: u32 = 1 << 0;
+
+    /// Normal shutdown notification
+    pub const SHN_NORMAL: u32 = 1 << 14;
+    pub const SHN_MASK: u32 = 3 << 14;
+
+    /// Queue entry sizes as powers of two: 64-byte submissions,
+    /// 16-byte completions
+    pub const IOSQES: u32 = 6 << 16;
+    pub const IOCQES: u32 = 4 << 20;
+}
+
+mod csts {
+    pub const RDY: u32 = 1 << 0;
+    pub const CFS: u32 = 1 << 1;
+    pub const SHST_MASK: u32 = 3 << 2;
+    pub const SHST_COMPLETE: u32 = 2 << 2;
+}
+
+/// Admin command opcodes
+mod admin_op {
+    pub const CREATE_SQ: u8 = 0x01;
+    pub const GET_LOG_PAGE: u8 = 0x02;
+    pub const CREATE_CQ: u8 = 0x05;
+    pub const IDENTIFY: u8 = 0x06;
+    pub const SET_FEATURES: u8 = 0x09;
+}
+
+/// NVM command opcodes
+mod io_op {
+    pub const FLUSH: u8 = 0x00;
+    pub const WRITE: u8 = 0x01;
+    pub const READ: u8 = 0x02;
+}
+
+/// Identify CNS values
+mod cns {
+    pub const NAMESPACE: u32 = 0x00;
+    pub const CONTROLLER: u32 = 0x01;
+    pub const ACTIVE_NAMESPACES: u32 = 0x02;
+}
+
+/// Number of Queues feature
+const FEATURE_NUM_QUEUES: u32 = 0x07;
+
+/// SMART / Health Information log page
+const LOG_HEALTH: u32 = 0x02;
+
+/// Namespace ID addressing every namespace
+const NSID_ALL: u32 = 0xFFFF_FFFF;
+
+/// Queue depths, capped by the controller's maximum
+const ADMIN_DEPTH: u16 = 32;
+const IO_DEPTH: u16 = 64;
+
+const SUBMISSION_SIZE: usize = 64;
+const COMPLETION_SIZE: usize = 16;
+
+/// Largest transfer per command, before the controller's own limit
+pub const MAX_TRANSFER: usize = 64 * 1024;
+
+/// Size of the I/O VMO offered to clients
+const IO_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Bounce buffer layout: the PRP list in the first page, data after
+const PRP_LIST_OFFSET: usize = 0;
+const DATA_OFFSET: usize = PAGE_SIZE;
+
+/// How long a command may take
+const COMMAND_TIMEOUT_NS: i64 = 5_000_000_000;
+
+/// Unit of `CAP.TO`
+const READY_TIMEOUT_UNIT_NS: i64 = 500_000_000;
+
+/// Log page data units are thousands of 512-byte blocks
+const DATA_UNIT_BYTES: u128 = 512_000;
+
+/// A submission queue entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Command {
+    pub opcode: u8,
+    pub nsid: u32,
+    pub prp1: u64,
+    pub prp2: u64,
+
+    /// Command dwords 10 to 15
+    pub cdw: [u32; 6],
+}
+
+impl Command {
+    fn new(opcode: u8, nsid: u32) -> Self {
+        Self {
+            opcode,
+            nsid,
+            ..Default::default()
+        }
+    }
+
+    /// Encode with command identifier `cid`
+    pub fn to_bytes(&self, cid: u16) -> [u8; SUBMISSION_SIZE] {
+        let mut buf = [0u8; SUBMISSION_SIZE];
+        let dw0 = self.opcode as u32 | (cid as u32) << 16;
+        buf[0..4].copy_from_slice(&dw0.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.nsid.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.prp1.to_le_bytes());
+        buf[32..40].copy_from_slice(&self.prp2.to_le_bytes());
+        for (i, dword) in self.cdw.iter().enumerate() {
+            buf[40 + i * 4..44 + i * 4].copy_from_slice(&dword.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// A completion queue entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Completion {
+    /// Command-specific result (dword 0)
+    pub result: u32,
+    pub sq_head: u16,
+    pub cid: u16,
+    pub phase: bool,
+
+    /// Status code type in bits 8-10, status code in bits 0-7
+    pub status: u16,
+}
+
+impl Completion {
+    /// Decode from the four dwords of an entry
+    pub fn from_dwords(dw: [u32; 4]) -> Self {
+        let status_field = (dw[3] >> 16) as u16;
+        Self {
+            result: dw[0],
+            sq_head: dw[2] as u16,
+            cid: dw[3] as u16,
+            phase: status_field & 1 != 0,
+            status: (status_field >> 1) & 0x7FF,
+        }
+    }
+
+    /// Map the status to a result
+    pub fn result(&self) -> Result<()> {
+        let code = self.status & 0xFF;
+        match self.status >> 8 {
+            0 => match code {
+                0x00 => Ok(()),
+                0x01 => Err(Error::new(Status::NotSupported)),
+                0x02 | 0x80 => Err(Error::new(Status::InvalidArgs)),
+                0x0B => Err(Error::new(Status::NotFound)),
+                _ => Err(Error::new(Status::IoError)),
+            },
+            // Command specific, such as an invalid queue or log page
+            1 => Err(Error::new(Status::InvalidArgs)),
+            _ => Err(Error::new(Status::IoError)),
+        }
+    }
+}
+
+/// PRP entries for `len` bytes of the bounce buffer
+///
+/// One or two pages fit in the command; longer transfers point PRP2 at
+/// the list, which holds the data pages after the first.
+fn prp_entries(data: u64, list: u64, len: usize) -> (u64, u64) {
+    match len.div_ceil(PAGE_SIZE) {
+        0 | 1 => (data, 0),
+        2 => (data, data + PAGE_SIZE as u64),
+        _ => (data, list),
+    }
+}
+
+/// Read a padded identity string out of Identify Controller data
+fn copy_text(dest: &mut [u8], src: &[u8]) {
+    dest.copy_from_slice(&src[..dest.len()]);
+}
+
+/// Little-endian 128-bit log page counter, saturated to 64 bits
+fn counter(log: &[u8], offset: usize) -> u64 {
+    let value = u128::from_le_bytes(log[offset..offset + 16].try_into().unwrap());
+    value.min(u64::MAX as u128) as u64
+}
+
+/// Fill `health` from the SMART / Health Information log page
+pub fn apply_health_log(health: &mut DeviceHealth, log: &[u8]) {
+    health.warnings = log[0];
+    let kelvin = u16::from_le_bytes([log[1], log[2]]);
+    if kelvin != 0 {
+        health.temperature = Some(kelvin as i16 - 273);
+    }
+    health.available_spare = Some(log[3]);
+    health.percentage_used = Some(log[5]);
+
+    let units = |offset| {
+        let value = u128::from_le_bytes(log[offset..offset + 16].try_into().unwrap());
+        value.saturating_mul(DATA_UNIT_BYTES).min(u64::MAX as u128) as u64
+    };
+    health.bytes_read = Some(units(32));
+    health.bytes_written = Some(units(48));
+    health.power_cycles = Some(counter(log, 112));
+    health.power_on_hours = Some(counter(log, 128));
+    health.unsafe_shutdowns = Some(counter(log, 144));
+    health.media_errors = Some(counter(log, 160));
+}
+
+/// A submission queue and its completion queue
+struct QueuePair {
+    id: u16,
+    depth: u16,
+
+    /// Submission queue in the first page, completion queue in the second
+    memory: DmaBuffer,
+
+    sq_tail: u16,
+    cq_head: u16,
+
+    /// Phase bit of entries the controller hasn't posted yet is the
+    /// opposite of this
+    phase: bool,
+
+    next_cid: u16,
+
+    /// Index of the queue's interrupt, if it has one
+    vector: Option<usize>,
+}
+
+impl QueuePair {
+    fn new(bti: &Bti, id: u16, depth: u16, vector: Option<usize>) -> Result<Self> {
+        let mut memory = DmaBuffer::create(bti, 2 * PAGE_SIZE)?;
+        memory.as_mut_slice().fill(0);
+        Ok(Self {
+            id,
+            depth,
+            memory,
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            next_cid: 0,
+            vector,
+        })
+    }
+
+    fn sq_phys(&self) -> u64 {
+        self.memory.phys()
+    }
+
+    fn cq_phys(&self) -> u64 {
+        self.memory.phys() + PAGE_SIZE as u64
+    }
+
+    /// Queue `command` and ring the tail doorbell
+    fn submit(&mut self, regs: &MmioBuffer, stride: usize, command: &Command) -> u16 {
+        let cid = self.next_cid;
+        self.next_cid = self.next_cid.wrapping_add(1);
+
+        let offset = self.sq_tail as usize * SUBMISSION_SIZE;
+        self.memory.as_mut_slice()[offset..offset + SUBMISSION_SIZE].copy_from_slice(&command.to_bytes(cid));
+        self.sq_tail = (self.sq_tail + 1) % self.depth;
+
+        fence(Ordering::SeqCst);
+        regs.write32(reg::DOORBELLS + 2 * self.id as usize * stride, self.sq_tail as u32);
+        cid
+    }
+
+    /// Take the next posted completion, if any
+    fn next_completion(&mut self, regs: &MmioBuffer, stride: usize) -> Option<Completion> {
+        let entry = unsafe {
+            let base = self.memory.as_ptr().add(PAGE_SIZE + self.cq_head as usize * COMPLETION_SIZE) as *const u32;
+            let mut dw = [0u32; 4];
+            for (i, dword) in dw.iter_mut().enumerate() {
+                *dword = u32::from_le(ptr::read_volatile(base.add(i)));
+            }
+            Completion::from_dwords(dw)
+        };
+        if entry.phase != self.phase {
+            return None;
+        }
+        fence(Ordering::Acquire);
+
+        self.cq_head += 1;
+        if self.cq_head == self.depth {
+            self.cq_head = 0;
+            self.phase = !self.phase;
+        }
+        regs.write32(reg::DOORBELLS + (2 * self.id as usize + 1) * stride, self.cq_head as u32);
+        Some(entry)
+    }
+
+    /// Wait for the completion of command `cid`
+    fn wait(&mut self, regs: &MmioBuffer, stride: usize, irq: Option<&Interrupt>, cid: u16) -> Result<Completion> {
+        let deadline = clock::monotonic() + COMMAND_TIMEOUT_NS;
+        loop {
+            match self.next_completion(regs, stride) {
+                Some(entry) if entry.cid == cid => return Ok(entry),
+                // Late completion of a command that timed out
+                Some(_) => continue,
+                None => {}
+            }
+            if let Some(irq) = irq {
+                if irq.try_wait().is_ok() {
+                    let _ = irq.ack();
+                    continue;
+                }
+            }
+            if clock::monotonic() > deadline {
+                return Err(Error::new(Status::TimedOut));
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Identity of the controller
+struct ControllerInfo {
+    health: DeviceHealth,
+
+    /// Number of namespaces
+    namespaces: u32,
+
+    /// Largest transfer the controller takes, in bytes (0 = no limit)
+    max_transfer: usize,
+
+    /// The controller has a volatile write cache
+    write_cache: bool,
+}
+
+/// Controller state shared by its namespaces
+struct Controller {
+    regs: MmioBuffer,
+
+    /// Distance between doorbells in bytes
+    stride: usize,
+
+    /// `CAP.TO` in nanoseconds
+    ready_timeout: i64,
+
+    admin: QueuePair,
+    io: QueuePair,
+    irqs: Vec<Interrupt>,
+    buffer: DmaBuffer,
+    info: ControllerInfo,
+    _bti: Bti,
+}
+
+impl Controller {
+    fn wait_status(&self, mask: u32, value: u32) -> Result<()> {
+        let deadline = clock::monotonic() + self.ready_timeout;
+        loop {
+            let status = self.regs.read32(reg::CSTS);
+            if status & mask == value {
+                return Ok(());
+            }
+            if status & csts::CFS != 0 {
+                return Err(Error::new(Status::IoError));
+            }
+            if clock::monotonic() > deadline {
+                return Err(Error::new(Status::TimedOut));
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Clear `CC.EN` and wait for the controller to stop
+    fn disable(&self) -> Result<()> {
+        let config = self.regs.read32(reg::CC);
+        if config & cc::EN != 0 {
+            self.regs.write32(reg::CC, config & !cc::EN);
+        }
+        self.wait_status(csts::RDY, 0)
+    }
+
+    /// Run an admin command
+    fn admin(&mut self, command: Command) -> Result<u32> {
+        let cid = self.admin.submit(&self.regs, self.stride, &command);
+        let irq = self.admin.vector.and_then(|v| self.irqs.get(v));
+        let entry = self.admin.wait(&self.regs, self.stride, irq, cid)?;
+        entry.result().map(|()| entry.result)
+    }
+
+    /// Run an I/O command
+    fn io(&mut self, command: Command) -> Result<()> {
+        let cid = self.io.submit(&self.regs, self.stride, &command);
+        let irq = self.io.vector.and_then(|v| self.irqs.get(v));
+        self.io.wait(&self.regs, self.stride, irq, cid)?.result()
+    }
+
+    /// Point a command at the first `len` bytes of the bounce buffer
+    fn with_data(&self, mut command: Command, len: usize) -> Command {
+        let data = self.buffer.phys() + DATA_OFFSET as u64;
+        let list = self.buffer.phys() + PRP_LIST_OFFSET as u64;
+        (command.prp1, command.prp2) = prp_entries(data, list, len);
+        command
+    }
+
+    fn data(&self, len: usize) -> &[u8] {
+        &self.buffer.as_slice()[DATA_OFFSET..DATA_OFFSET + len]
+    }
+
+    fn data_mut(&mut self, len: usize) -> &mut [u8] {
+        &mut self.buffer.as_mut_slice()[DATA_OFFSET..DATA_OFFSET + len]
+    }
+
+    /// Run Identify with `cns` for `nsid` into the bounce buffer
+    fn identify(&mut self, cns: u32, nsid: u32) -> Result<&[u8]> {
+        let mut command = Command::new(admin_op::IDENTIFY, nsid);
+        command.cdw[0] = cns;
+        let command = self.with_data(command, PAGE_SIZE);
+        self.admin(command)?;
+        Ok(self.data(PAGE_SIZE))
+    }
+
+    fn identify_controller(&mut self) -> Result<()> {
+        let data = self.identify(cns::CONTROLLER, 0)?;
+        let mut health = DeviceHealth::new();
+        copy_text(&mut health.serial, &data[4..]);
+        copy_text(&mut health.model, &data[24..]);
+        copy_text(&mut health.firmware, &data[64..]);
+        let mdts = data[77];
+        let namespaces = u32::from_le_bytes(data[516..520].try_into().unwrap());
+        let write_cache = data[525] & 1 != 0;
+
+        self.info = ControllerInfo {
+            health,
+            namespaces,
+            max_transfer: if mdts == 0 { 0 } else { PAGE_SIZE << mdts.min(20) },
+            write_cache,
+        };
+        Ok(())
+    }
+
+    /// Ask for one I/O queue pair and create it
+    fn create_io_queues(&mut self) -> Result<()> {
+        let mut command = Command::new(admin_op::SET_FEATURES, 0);
+        command.cdw[0] = FEATURE_NUM_QUEUES;
+        // Both counts are 0-based
+        command.cdw[1] = 0;
+        self.admin(command)?;
+
+        let size = (self.io.depth as u32 - 1) << 16 | self.io.id as u32;
+        let vector = self.io.vector;
+
+        let mut command = Command::new(admin_op::CREATE_CQ, 0);
+        command.prp1 = self.io.cq_phys();
+        command.cdw[0] = size;
+        // Physically contiguous, interrupts on the queue's vector
+        command.cdw[1] = 1 | (vector.is_some() as u32) << 1 | (vector.unwrap_or(0) as u32) << 16;
+        self.admin(command)?;
+
+        let mut command = Command::new(admin_op::CREATE_SQ, 0);
+        command.prp1 = self.io.sq_phys();
+        command.cdw[0] = size;
+        command.cdw[1] = 1 | (self.io.id as u32) << 16;
+        self.admin(command).map(|_| ())
+    }
+
+    /// IDs of the active namespaces
+    fn namespace_ids(&mut self) -> Vec<u32> {
+        match self.identify(cns::ACTIVE_NAMESPACES, 0) {
+            Ok(data) => data
+                .chunks_exact(4)
+                .map(|id| u32::from_le_bytes(id.try_into().unwrap()))
+                .take_while(|&id| id != 0)
+                .collect(),
+            // Controllers before NVMe 1.1 can't list them; try every ID
+            Err(_) => (1..=self.info.namespaces.min(1024)).collect(),
+        }
+    }
+
+    /// Geometry of namespace `nsid`, or `None` if it is inactive or
+    /// formatted in a way the driver can't use
+    fn namespace_info(&mut self, nsid: u32) -> Result<Option<BlockInfo>> {
+        let controller_max = self.info.max_transfer;
+        let data = self.identify(cns::NAMESPACE, nsid)?;
+        let size = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        let flbas = data[26];
+        let format = 128 + (flbas & 0xF) as usize * 4;
+        let metadata = u16::from_le_bytes([data[format], data[format + 1]]);
+        let block_shift = data[format + 2] as u32;
+
+        let mut max_transfer = MAX_TRANSFER;
+        if controller_max != 0 {
+            max_transfer = max_transfer.min(controller_max);
+        }
+        // Metadata interleaved with the data would land in the bounce
+        // buffer between blocks
+        let extended = flbas & (1 << 4) != 0 && metadata != 0;
+        if size == 0 || extended || !(9..=12).contains(&block_shift) || (1 << block_shift) > max_transfer {
+            return Ok(None);
+        }
+        Ok(Some(BlockInfo {
+            block_size: 1 << block_shift,
+            max_transfer: max_transfer as u32,
+            block_count: size,
+        }))
+    }
+
+    /// Read or write `len` bytes of the bounce buffer at `lba`
+    fn transfer(&mut self, opcode: u8, nsid: u32, info: &BlockInfo, lba: u64, len: usize) -> Result<()> {
+        let blocks = (len / info.block_size as usize) as u32;
+        let mut command = Command::new(opcode, nsid);
+        command.cdw[0] = lba as u32;
+        command.cdw[1] = (lba >> 32) as u32;
+        command.cdw[2] = blocks - 1;
+        let command = self.with_data(command, len);
+        self.io(command)
+    }
+
+    fn health(&mut self) -> Result<DeviceHealth> {
+        let mut health = self.info.health;
+        let mut command = Command::new(admin_op::GET_LOG_PAGE, NSID_ALL);
+        // 512 bytes, as a 0-based dword count
+        command.cdw[0] = LOG_HEALTH | (512 / 4 - 1) << 16;
+        let command = self.with_data(command, 512);
+        self.admin(command)?;
+        apply_health_log(&mut health, self.data(512));
+        Ok(health)
+    }
+
+    /// Tell the controller to shut down and disable it
+    fn shutdown(&self) -> Result<()> {
+        let config = self.regs.read32(reg::CC);
+        self.regs.write32(reg::CC, (config & !cc::SHN_MASK) | cc::SHN_NORMAL);
+        self.wait_status(csts::SHST_MASK, csts::SHST_COMPLETE)?;
+        self.disable()
+    }
+}
+
+/// Enable up to two interrupts, MSI-X if the device has it
+fn setup_interrupts(device: &PciDevice) -> Vec<Interrupt> {
+    let count = match device.query_irq_mode(irq_mode::MSI_X) {
+        Ok(n) if n > 0 && device.set_irq_mode(irq_mode::MSI_X, n.min(2)).is_ok() => n.min(2),
+        _ => match device.configure_irq() {
+            Ok(_) => 1,
+            Err(_) => 0,
+        },
+    };
+    (0..count as i32).map_while(|which| device.map_interrupt(which).ok()).collect()
+}
+
+/// An NVMe namespace
+pub struct Namespace {
+    controller: Rc<RefCell<Controller>>,
+    id: u32,
+    info: BlockInfo,
+}
+
+impl Namespace {
+    /// Namespace ID
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl BlockDevice for Namespace {
+    fn info(&self) -> BlockInfo {
+        self.info
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let mut controller = self.controller.borrow_mut();
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(self.info.max_transfer as usize) {
+            controller.transfer(io_op::READ, self.id, &self.info, lba, chunk.len())?;
+            chunk.copy_from_slice(controller.data(chunk.len()));
+            lba += (chunk.len() / self.info.block_size as usize) as u64;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let mut controller = self.controller.borrow_mut();
+        let mut lba = lba;
+        for chunk in buf.chunks(self.info.max_transfer as usize) {
+            controller.data_mut(chunk.len()).copy_from_slice(chunk);
+            controller.transfer(io_op::WRITE, self.id, &self.info, lba, chunk.len())?;
+            lba += (chunk.len() / self.info.block_size as usize) as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut controller = self.controller.borrow_mut();
+        if !controller.info.write_cache {
+            return Ok(());
+        }
+        controller.io(Command::new(io_op::FLUSH, self.id))
+    }
+
+    /// Health of the whole controller; NVMe keeps it per controller
+    fn health(&mut self) -> Result<DeviceHealth> {
+        self.controller.borrow_mut().health()
+    }
+}
+
+/// An NVMe controller
+pub struct Nvme {
+    controller: Rc<RefCell<Controller>>,
+    disks: DiskSet<Namespace>,
+}
+
+impl Nvme {
+    /// Take over the controller behind `device` and find its namespaces
+    pub fn new(device: &PciDevice) -> Result<Self> {
+        let regs = device.map_bar(0)?;
+        device.enable_bus_master(true)?;
+
+        let cap = regs.read32(reg::CAP) as u64 | (regs.read32(reg::CAP + 4) as u64) << 32;
+        let max_entries = (cap & 0xFFFF) as u16 + 1;
+        let stride = 4 << ((cap >> 32) & 0xF);
+        let nvm_command_set = cap & (1 << 37) != 0;
+        let min_page_shift = 12 + ((cap >> 48) & 0xF);
+        if !nvm_command_set || min_page_shift != 12 {
+            return Err(Error::new(Status::NotSupported));
+        }
+
+        let bti = Bti::create(0)?;
+        let irqs = setup_interrupts(device);
+        // The admin queue takes the first vector, the I/O queue the last
+        let admin = QueuePair::new(&bti, 0, ADMIN_DEPTH.min(max_entries), (!irqs.is_empty()).then_some(0))?;
+        let io = QueuePair::new(&bti, 1, IO_DEPTH.min(max_entries), irqs.len().checked_sub(1))?;
+        let mut buffer = DmaBuffer::create(&bti, DATA_OFFSET + MAX_TRANSFER)?;
+
+        // The PRP list never changes: it names the data pages after the
+        // first
+        let data = buffer.phys() + DATA_OFFSET as u64;
+        for (i, entry) in buffer.as_mut_slice()[PRP_LIST_OFFSET..PAGE_SIZE]
+            .chunks_exact_mut(8)
+            .take(MAX_TRANSFER / PAGE_SIZE - 1)
+            .enumerate()
+        {
+            entry.copy_from_slice(&(data + ((i + 1) * PAGE_SIZE) as u64).to_le_bytes());
+        }
+
+        let mut controller = Controller {
+            regs,
+            stride,
+            ready_timeout: ((cap >> 24) & 0xFF).max(1) as i64 * READY_TIMEOUT_UNIT_NS,
+            admin,
+            io,
+            irqs,
+            buffer,
+            info: ControllerInfo {
+                health: DeviceHealth::new(),
+                namespaces: 0,
+                max_transfer: 0,
+                write_cache: false,
+            },
+            _bti: bti,
+        };
+
+        controller.disable()?;
+        let depth = controller.admin.depth as u32 - 1;
+        controller.regs.write32(reg::AQA, depth << 16 | depth);
+        write64(&controller.regs, reg::ASQ, controller.admin.sq_phys());
+        write64(&controller.regs, reg::ACQ, controller.admin.cq_phys());
+        controller.regs.write32(reg::CC, cc::EN | cc::IOSQES | cc::IOCQES);
+        controller.wait_status(csts::RDY, csts::RDY)?;
+
+        controller.identify_controller()?;
+        controller.create_io_queues()?;
+
+        let mut namespaces = Vec::new();
+        for nsid in controller.namespace_ids() {
+            // A namespace that fails to identify doesn't stop the others
+            if let Ok(Some(info)) = controller.namespace_info(nsid) {
+                namespaces.push((nsid, info));
+            }
+        }
+
+        let controller = Rc::new(RefCell::new(controller));
+        let mut disks = DiskSet::new();
+        for (id, info) in namespaces {
+            disks.push(Namespace {
+                controller: controller.clone(),
+                id,
+                info,
+            });
+        }
+        Ok(Self { controller, disks })
+    }
+
+    /// The namespaces found
+    pub fn namespaces(&self) -> impl Iterator<Item = &Namespace> {
+        self.disks.devices()
+    }
+
+    /// Shut the controller down
+    pub fn stop(self) -> Result<()> {
+        drop(self.disks);
+        self.controller.borrow().shutdown()
+    }
+
+    /// Connect a client to `instance`
+    pub fn open(&mut self, instance: u32) -> Result<Connection> {
+        self.disks.open(instance, IO_BUFFER_SIZE)
+    }
+
+    /// Serve every open instance
+    pub fn poll(&mut self) -> Result<bool> {
+        self.disks.poll()
+    }
+}
+
+/// Write a 64-bit register as two 32-bit halves, low first
+fn write64(regs: &MmioBuffer, offset: usize, value: u64) {
+    regs.write32(offset, value as u32);
+    regs.write32(offset + 4, (value >> 32) as u32);
+}
+
+/// ============================================================================
+/// Driver
+/// ============================================================================
+
+/// NVMe driver for the driver host
+pub struct NvmeDriver {
+    device: Option<PciDevice>,
+    controller: Option<Nvme>,
+}
+
+impl NvmeDriver {
+    fn matches(descriptor: &DeviceDescriptor) -> bool {
+        descriptor.protocol == bus_protocol::PCI
+            && descriptor.base_class == PCI_CLASS_STORAGE
+            && descriptor.sub_class == PCI_SUBCLASS_NVM
+            && descriptor.prog_if == PCI_PROG_IF_NVME
+    }
+
+    fn create() -> Box<dyn Driver> {
+        Box::new(NvmeDriver {
+            device: None,
+            controller: None,
+        })
+    }
+}
+
+impl Driver for NvmeDriver {
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()> {
+        let info = PciDeviceInfo {
+            vendor_id: descriptor.vendor_id,
+            device_id: descriptor.device_id,
+            base_class: descriptor.base_class,
+            sub_class: descriptor.sub_class,
+            prog_if: descriptor.prog_if,
+            ..Default::default()
+        };
+        let device = unsafe { PciDevice::from_handle(device, info) };
+        self.controller = Some(Nvme::new(&device)?);
+        self.device = Some(device);
+        Ok(())
+    }
+
+    fn unbind(&mut self) -> Result<()> {
+        if let Some(controller) = self.controller.take() {
+            controller.stop()?;
+        }
+        if let Some(device) = self.device.take() {
+            device.enable_bus_master(false)?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, instance: u32) -> Result<Connection> {
+        self.controller.as_mut().ok_or(Error::new(Status::BadState))?.open(instance)
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        match self.controller.as_mut() {
+            Some(controller) => controller.poll(),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Driver table entry
+pub const DRIVER: DriverEntry = DriverEntry {
+    name: "nvme",
+    matches: NvmeDriver::matches,
+    create: NvmeDriver::create,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let mut descriptor = DeviceDescriptor {
+            protocol: bus_protocol::PCI,
+            vendor_id: 0x1b36,
+            device_id: 0x0010,
+            base_class: 0x01,
+            sub_class: 0x08,
+            prog_if: 0x02,
+            revision_id: 2,
+        };
+        assert!(NvmeDriver::matches(&descriptor));
+        // AHCI
+        descriptor.sub_class = 0x06;
+        descriptor.prog_if = 0x01;
+        assert!(!NvmeDriver::matches(&descriptor));
+    }
+
+    #[test]
+    fn test_command_encoding() {
+        let mut command = Command::new(io_op::READ, 1);
+        command.prp1 = 0x1000;
+        command.prp2 = 0x2000;
+        command.cdw[0] = 0x1234;
+        command.cdw[2] = 7;
+        let bytes = command.to_bytes(0xBEEF);
+        assert_eq!(&bytes[0..4], &[0x02, 0x00, 0xEF, 0xBE]);
+        assert_eq!(&bytes[4..8], &[1, 0, 0, 0]);
+        assert_eq!(u64::from_le_bytes(bytes[24..32].try_into().unwrap()), 0x1000);
+        assert_eq!(u64::from_le_bytes(bytes[32..40].try_into().unwrap()), 0x2000);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 0x1234);
+        assert_eq!(u32::from_le_bytes(bytes[48..52].try_into().unwrap()), 7);
+    }
+
+    #[test]
+    fn test_completion() {
+        // Phase set, success
+        let entry = Completion::from_dwords([42, 0, 0x0001_0005, 0x0001_0009]);
+        assert_eq!(entry.result, 42);
+        assert_eq!(entry.sq_head, 5);
+        assert_eq!(entry.cid, 9);
+        assert!(entry.phase);
+        assert!(entry.result().is_ok());
+
+        // Generic invalid namespace, phase clear
+        let entry = Completion::from_dwords([0, 0, 0, (0x0B << 1) << 16]);
+        assert!(!entry.phase);
+        assert_eq!(entry.result().unwrap_err().status(), Status::NotFound);
+
+        // Media error type
+        let entry = Completion::from_dwords([0, 0, 0, ((2 << 8 | 0x81) << 1 | 1) << 16]);
+        assert_eq!(entry.result().unwrap_err().status(), Status::IoError);
+    }
+
+    #[test]
+    fn test_prp_entries() {
+        let (data, list) = (0x10_0000, 0xF_F000);
+        assert_eq!(prp_entries(data, list, 512), (data, 0));
+        assert_eq!(prp_entries(data, list, 4096), (data, 0));
+        assert_eq!(prp_entries(data, list, 4097), (data, data + 4096));
+        assert_eq!(prp_entries(data, list, 65536), (data, list));
+    }
+
+    #[test]
+    fn test_health_log() {
+        let mut log = [0u8; 512];
+        log[0] = 0;
+        log[1..3].copy_from_slice(&310u16.to_le_bytes());
+        log[3] = 100;
+        log[5] = 3;
+        log[32..48].copy_from_slice(&2u128.to_le_bytes());
+        log[48..64].copy_from_slice(&u128::MAX.to_le_bytes());
+        log[112..128].copy_from_slice(&17u128.to_le_bytes());
+        log[128..144].copy_from_slice(&1000u128.to_le_bytes());
+        log[144..160].copy_from_slice(&(1u128 << 70).to_le_bytes());
+
+        let mut health = DeviceHealth::new();
+        apply_health_log(&mut health, &log);
+        assert!(health.healthy());
+        assert_eq!(health.temperature, Some(37));
+        assert_eq!(health.available_spare, Some(100));
+        assert_eq!(health.percentage_used, Some(3));
+        assert_eq!(health.bytes_read, Some(1_024_000));
+        assert_eq!(health.bytes_written, Some(u64::MAX));
+        assert_eq!(health.power_cycles, Some(17));
+        assert_eq!(health.power_on_hours, Some(1000));
+        assert_eq!(health.unsafe_shutdowns, Some(u64::MAX));
+        assert_eq!(health.media_errors, Some(0));
+
+        log[0] = 1 << 1;
+        apply_health_log(&mut health, &log);
+        assert!(!health.healthy());
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! USB Requests and Descriptors
+//!
+//! Setup packets for the standard and HID class requests used during
+//! enumeration, and parsers for the device and configuration
+//! descriptors. A configuration descriptor is read whole, with its
+//! interface, class and endpoint descriptors following it; `Configuration`
+//! keeps the interfaces and their endpoints and skips everything else.
+
+use alloc::vec::Vec;
+
+/// `bmRequestType` bits
+pub mod request_type {
+    /// Data flows from the device
+    pub const DIR_IN: u8 = 0x80;
+
+    pub const STANDARD: u8 = 0x00;
+    pub const CLASS: u8 = 0x20;
+
+    pub const DEVICE: u8 = 0x00;
+    pub const INTERFACE: u8 = 0x01;
+    pub const ENDPOINT: u8 = 0x02;
+}
+
+/// Standard requests
+pub mod request {
+    pub const GET_STATUS: u8 = 0x00;
+    pub const CLEAR_FEATURE: u8 = 0x01;
+    pub const SET_FEATURE: u8 = 0x03;
+    pub const GET_DESCRIPTOR: u8 = 0x06;
+    pub const SET_CONFIGURATION: u8 = 0x09;
+}
+
+/// HID class requests
+pub mod hid_request {
+    pub const SET_REPORT: u8 = 0x09;
+    pub const SET_IDLE: u8 = 0x0A;
+    pub const SET_PROTOCOL: u8 = 0x0B;
+}
+
+/// Descriptor types
+pub mod descriptor_type {
+    pub const DEVICE: u8 = 0x01;
+    pub const CONFIGURATION: u8 = 0x02;
+    pub const STRING: u8 = 0x03;
+    pub const INTERFACE: u8 = 0x04;
+    pub const ENDPOINT: u8 = 0x05;
+    pub const HID: u8 = 0x21;
+}
+
+/// Interface classes
+pub mod class {
+    pub const HID: u8 = 0x03;
+    pub const HUB: u8 = 0x09;
+}
+
+/// Endpoint transfer types (`bmAttributes` bits 1:0)
+pub mod transfer_type {
+    pub const CONTROL: u8 = 0;
+    pub const ISOCHRONOUS: u8 = 1;
+    pub const BULK: u8 = 2;
+    pub const INTERRUPT: u8 = 3;
+}
+
+/// A control request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+
+    /// Bytes in the data stage
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// GET_DESCRIPTOR for descriptor `index` of type `kind`
+    pub fn get_descriptor(kind: u8, index: u8, length: u16) -> Self {
+        Self {
+            request_type: request_type::DIR_IN | request_type::STANDARD | request_type::DEVICE,
+            request: request::GET_DESCRIPTOR,
+            value: (kind as u16) << 8 | index as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// SET_CONFIGURATION
+    pub fn set_configuration(value: u8) -> Self {
+        Self {
+            request_type: request_type::STANDARD | request_type::DEVICE,
+            request: request::SET_CONFIGURATION,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// A HID class request to `interface` without a data stage, or with
+    /// `length` bytes sent to the device
+    pub fn hid(request: u8, value: u16, interface: u8, length: u16) -> Self {
+        Self {
+            request_type: request_type::CLASS | request_type::INTERFACE,
+            request,
+            value,
+            index: interface as u16,
+            length,
+        }
+    }
+
+    /// Whether the data stage reads from the device
+    pub fn is_in(&self) -> bool {
+        self.request_type & request_type::DIR_IN != 0
+    }
+
+    /// The packet as the eight bytes sent on the wire, little-endian
+    pub fn to_u64(&self) -> u64 {
+        self.request_type as u64
+            | (self.request as u64) << 8
+            | (self.value as u64) << 16
+            | (self.index as u64) << 32
+            | (self.length as u64) << 48
+    }
+}
+
+/// Device descriptor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// `bcdUSB`
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+
+    /// Largest packet on endpoint 0 (an exponent for SuperSpeed devices)
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub num_configurations: u8,
+}
+
+impl DeviceInfo {
+    /// Size of the descriptor
+    pub const SIZE: usize = 18;
+
+    /// Parse a device descriptor
+    ///
+    /// The first 8 bytes, as read before endpoint 0's packet size is
+    /// known, are enough for `max_packet_size0`; the other fields then
+    /// read as zero.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 8 || buf[1] != descriptor_type::DEVICE {
+            return None;
+        }
+        let mut full = [0u8; Self::SIZE];
+        let len = buf.len().min(Self::SIZE);
+        full[..len].copy_from_slice(&buf[..len]);
+        Some(Self {
+            usb_version: u16::from_le_bytes([full[2], full[3]]),
+            class: full[4],
+            subclass: full[5],
+            protocol: full[6],
+            max_packet_size0: full[7],
+            vendor_id: u16::from_le_bytes([full[8], full[9]]),
+            product_id: u16::from_le_bytes([full[10], full[11]]),
+            num_configurations: full[17],
+        })
+    }
+}
+
+/// Endpoint descriptor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Endpoint {
+    /// `bEndpointAddress`: number in bits 3:0, direction in bit 7
+    pub address: u8,
+    pub attributes: u8,
+    pub max_packet_size: u16,
+
+    /// `bInterval`, in the units of the device's speed
+    pub interval: u8,
+}
+
+impl Endpoint {
+    pub fn number(&self) -> u8 {
+        self.address & 0x0F
+    }
+
+    pub fn is_in(&self) -> bool {
+        self.address & 0x80 != 0
+    }
+
+    /// `transfer_type` value
+    pub fn transfer_type(&self) -> u8 {
+        self.attributes & 0x03
+    }
+}
+
+/// Interface descriptor and its endpoints
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interface {
+    pub number: u8,
+    pub alternate: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub endpoints: Vec<Endpoint>,
+}
+
+/// Configuration descriptor and its interfaces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Configuration {
+    /// `bConfigurationValue`, passed to SET_CONFIGURATION
+    pub value: u8,
+    pub interfaces: Vec<Interface>,
+}
+
+impl Configuration {
+    /// Size of the configuration descriptor itself
+    pub const HEADER_SIZE: usize = 9;
+
+    /// `wTotalLength` of a configuration descriptor header
+    pub fn total_length(header: &[u8]) -> Option<u16> {
+        if header.len() < 4 || header[1] != descriptor_type::CONFIGURATION {
+            return None;
+        }
+        Some(u16::from_le_bytes([header[2], header[3]]))
+    }
+
+    /// Parse a configuration and the descriptors following it
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let total = Self::total_length(buf)? as usize;
+        if buf.len() < Self::HEADER_SIZE || total < Self::HEADER_SIZE {
+            return None;
+        }
+        let buf = &buf[..total.min(buf.len())];
+        let mut config = Self {
+            value: buf[5],
+            interfaces: Vec::new(),
+        };
+
+        let mut offset = buf[0] as usize;
+        while offset + 2 <= buf.len() {
+            let len = buf[offset] as usize;
+            if len < 2 || offset + len > buf.len() {
+                break;
+            }
+            let desc = &buf[offset..offset + len];
+            match desc[1] {
+                descriptor_type::INTERFACE if len >= 9 => config.interfaces.push(Interface {
+                    number: desc[2],
+                    alternate: desc[3],
+                    class: desc[5],
+                    subclass: desc[6],
+                    protocol: desc[7],
+                    endpoints: Vec::new(),
+                }),
+                descriptor_type::ENDPOINT if len >= 7 => {
+                    if let Some(interface) = config.interfaces.last_mut() {
+                        interface.endpoints.push(Endpoint {
+                            address: desc[2],
+                            attributes: desc[3],
+                            max_packet_size: u16::from_le_bytes([desc[4], desc[5]]),
+                            interval: desc[6],
+                        });
+                    }
+                }
+                _ => {}
+            }
+            offset += len;
+        }
+        Some(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Configuration of QEMU's usb-kbd
+    const KEYBOARD_CONFIG: [u8; 34] = [
+        0x09, 0x02, 0x22, 0x00, 0x01, 0x01, 0x06, 0xa0, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, // interface
+        0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x3f, 0x00, // HID
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x07, // endpoint
+    ];
+
+    #[test]
+    fn test_setup_packet() {
+        let setup = SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, 18);
+        assert!(setup.is_in());
+        assert_eq!(setup.to_u64().to_le_bytes(), [0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x12, 0x00]);
+
+        let setup = SetupPacket::hid(hid_request::SET_PROTOCOL, 0, 2, 0);
+        assert!(!setup.is_in());
+        assert_eq!(setup.to_u64().to_le_bytes(), [0x21, 0x0b, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_parse_device() {
+        let desc = [
+            0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x40, 0x27, 0x06, 0x01, 0x00, 0x00, 0x00, 0x01, 0x02,
+            0x03, 0x01,
+        ];
+        let info = DeviceInfo::parse(&desc).unwrap();
+        assert_eq!(info.usb_version, 0x0200);
+        assert_eq!(info.max_packet_size0, 64);
+        assert_eq!((info.vendor_id, info.product_id), (0x0627, 0x0001));
+        assert_eq!(info.num_configurations, 1);
+
+        let short = DeviceInfo::parse(&desc[..8]).unwrap();
+        assert_eq!(short.max_packet_size0, 64);
+        assert_eq!(short.vendor_id, 0);
+        assert_eq!(DeviceInfo::parse(&KEYBOARD_CONFIG), None);
+    }
+
+    #[test]
+    fn test_parse_configuration() {
+        assert_eq!(Configuration::total_length(&KEYBOARD_CONFIG), Some(34));
+        let config = Configuration::parse(&KEYBOARD_CONFIG).unwrap();
+        assert_eq!(config.value, 1);
+        assert_eq!(config.interfaces.len(), 1);
+
+        let interface = &config.interfaces[0];
+        assert_eq!((interface.class, interface.subclass, interface.protocol), (class::HID, 1, 1));
+        assert_eq!(interface.endpoints.len(), 1);
+        let endpoint = interface.endpoints[0];
+        assert_eq!(endpoint.number(), 1);
+        assert!(endpoint.is_in());
+        assert_eq!(endpoint.transfer_type(), transfer_type::INTERRUPT);
+        assert_eq!(endpoint.max_packet_size, 8);
+        assert_eq!(endpoint.interval, 7);
+
+        // A truncated trailing descriptor is ignored
+        assert_eq!(Configuration::parse(&KEYBOARD_CONFIG[..30]).unwrap().interfaces[0].endpoints.len(), 0);
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! HID Boot Keyboards
+//!
+//! In the boot protocol a keyboard reports its whole state in 8 bytes:
+//! a modifier bitmap, a reserved byte and up to six usages of keys held
+//! down. Presses and releases are found by comparing each report with
+//! the previous one. Lock keys toggle state kept here, which the driver
+//! mirrors on the keyboard's LEDs.
+
+use libddk::input::{key, modifiers};
+use libddk::KeyEvent;
+
+/// Interface subclass supporting the boot protocol
+pub const SUBCLASS_BOOT: u8 = 1;
+
+/// Boot interface protocol of keyboards
+pub const PROTOCOL_KEYBOARD: u8 = 1;
+
+/// Size of a boot keyboard report
+pub const REPORT_SIZE: usize = 8;
+
+/// SET_PROTOCOL value selecting the boot protocol
+pub const BOOT_PROTOCOL: u16 = 0;
+
+/// SET_REPORT value addressing output report 0, the LEDs
+pub const LED_REPORT: u16 = 0x0200;
+
+/// Output report bits
+pub mod led {
+    pub const NUM_LOCK: u8 = 1 << 0;
+    pub const CAPS_LOCK: u8 = 1 << 1;
+    pub const SCROLL_LOCK: u8 = 1 << 2;
+}
+
+/// Usage filling a report when too many keys are down
+const ERROR_ROLLOVER: u8 = 0x01;
+
+/// Key state of one boot keyboard
+pub struct BootKeyboard {
+    last: [u8; REPORT_SIZE],
+
+    /// `modifiers` lock bits
+    locks: u16,
+}
+
+impl BootKeyboard {
+    pub fn new() -> Self {
+        Self {
+            last: [0; REPORT_SIZE],
+            locks: 0,
+        }
+    }
+
+    /// Decode a report, passing each press and release to `emit`
+    ///
+    /// Returns true if a lock key toggled and the LEDs need updating.
+    pub fn report(&mut self, report: &[u8], mut emit: impl FnMut(KeyEvent)) -> bool {
+        if report.len() < REPORT_SIZE {
+            return false;
+        }
+        let mut current = [0u8; REPORT_SIZE];
+        current.copy_from_slice(&report[..REPORT_SIZE]);
+        // Rollover reports say nothing about which keys changed
+        if current[2..].contains(&ERROR_ROLLOVER) {
+            return false;
+        }
+
+        let mut mods = self.last[0] as u16 | self.locks;
+        let changed = self.last[0] ^ current[0];
+        for bit in 0..8 {
+            if changed & (1 << bit) != 0 {
+                let pressed = current[0] & (1 << bit) != 0;
+                mods ^= 1 << bit;
+                emit(KeyEvent::new(key::LEFT_CTRL + bit, pressed, mods));
+            }
+        }
+
+        for &usage in self.last[2..].iter().filter(|&&u| u != 0) {
+            if !current[2..].contains(&usage) {
+                emit(KeyEvent::new(usage as u16, false, mods));
+            }
+        }
+
+        let locks = self.locks;
+        for &usage in current[2..].iter().filter(|&&u| u != 0) {
+            if self.last[2..].contains(&usage) {
+                continue;
+            }
+            self.locks ^= match usage as u16 {
+                key::CAPS_LOCK => modifiers::CAPS_LOCK,
+                key::NUM_LOCK => modifiers::NUM_LOCK,
+                key::SCROLL_LOCK => modifiers::SCROLL_LOCK,
+                _ => 0,
+            };
+            mods = current[0] as u16 | self.locks;
+            emit(KeyEvent::new(usage as u16, true, mods));
+        }
+
+        self.last = current;
+        self.locks != locks
+    }
+
+    /// Output report lighting the LEDs of the active locks
+    pub fn leds(&self) -> u8 {
+        let mut leds = 0;
+        if self.locks & modifiers::NUM_LOCK != 0 {
+            leds |= led::NUM_LOCK;
+        }
+        if self.locks & modifiers::CAPS_LOCK != 0 {
+            leds |= led::CAPS_LOCK;
+        }
+        if self.locks & modifiers::SCROLL_LOCK != 0 {
+            leds |= led::SCROLL_LOCK;
+        }
+        leds
+    }
+}
+
+impl Default for BootKeyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn decode(keyboard: &mut BootKeyboard, report: [u8; 8]) -> (Vec<KeyEvent>, bool) {
+        let mut events = Vec::new();
+        let leds = keyboard.report(&report, |event| events.push(event));
+        (events, leds)
+    }
+
+    #[test]
+    fn test_press_and_release() {
+        let mut keyboard = BootKeyboard::new();
+        let (events, _) = decode(&mut keyboard, [0x02, 0, 0x04, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            events,
+            [
+                KeyEvent::new(key::LEFT_SHIFT, true, modifiers::LEFT_SHIFT),
+                KeyEvent::new(key::A, true, modifiers::LEFT_SHIFT),
+            ]
+        );
+
+        // Holding a key repeats nothing
+        let (events, _) = decode(&mut keyboard, [0x02, 0, 0x04, 0, 0, 0, 0, 0]);
+        assert!(events.is_empty());
+
+        let (events, _) = decode(&mut keyboard, [0x00, 0, 0x05, 0x04, 0, 0, 0, 0]);
+        assert_eq!(events, [KeyEvent::new(key::LEFT_SHIFT, false, 0), KeyEvent::new(key::A + 1, true, 0)]);
+
+        let (events, _) = decode(&mut keyboard, [0; 8]);
+        assert_eq!(events, [KeyEvent::new(key::A + 1, false, 0), KeyEvent::new(key::A, false, 0)]);
+    }
+
+    #[test]
+    fn test_locks() {
+        let mut keyboard = BootKeyboard::new();
+        let (events, leds) = decode(&mut keyboard, [0, 0, 0x39, 0, 0, 0, 0, 0]);
+        assert!(leds);
+        assert_eq!(events, [KeyEvent::new(key::CAPS_LOCK, true, modifiers::CAPS_LOCK)]);
+        assert_eq!(keyboard.leds(), led::CAPS_LOCK);
+
+        let (_, leds) = decode(&mut keyboard, [0; 8]);
+        assert!(!leds);
+        let (events, _) = decode(&mut keyboard, [0, 0, 0x04, 0, 0, 0, 0, 0]);
+        assert_eq!(events, [KeyEvent::new(key::A, true, modifiers::CAPS_LOCK)]);
+
+        decode(&mut keyboard, [0; 8]);
+        let (_, leds) = decode(&mut keyboard, [0, 0, 0x39, 0, 0, 0, 0, 0]);
+        assert!(leds);
+        assert_eq!(keyboard.leds(), 0);
+    }
+
+    #[test]
+    fn test_rollover_ignored() {
+        let mut keyboard = BootKeyboard::new();
+        decode(&mut keyboard, [0, 0, 0x04, 0, 0, 0, 0, 0]);
+        let (events, _) = decode(&mut keyboard, [0, 0, 1, 1, 1, 1, 1, 1]);
+        assert!(events.is_empty());
+        let (events, _) = decode(&mut keyboard, [0, 0, 0x04, 0, 0, 0, 0, 0]);
+        assert!(events.is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! USB Drivers
+//!
+//! The start of the USB stack:
+//! - xHCI host controller driver: command, event and transfer rings,
+//!   root port enumeration, control and interrupt transfers
+//! - Standard descriptors and requests
+//! - HID boot-protocol keyboards, published through the DDK input class
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::DriverEntry;
+//!
+//! static DRIVERS: [DriverEntry; 1] = [usb::xhci::DRIVER];
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod descriptor;
+pub mod hid;
+pub mod ring;
+pub mod xhci;
+
+// Re-export commonly used types
+pub use descriptor::{Configuration, DeviceInfo, SetupPacket};
+pub use hid::BootKeyboard;
+pub use ring::{EventRing, Ring, Trb};
+pub use xhci::Xhci;
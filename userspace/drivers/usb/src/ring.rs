@@ -0,0 +1,364 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! xHCI Rings
+//!
+//! The controller and driver exchange 16-byte transfer request blocks
+//! (TRBs) through rings in DMA memory. Ownership of each TRB is given by
+//! its cycle bit: the producer writes TRBs with its current cycle state
+//! and the consumer takes them while the bit matches its own.
+//!
+//! - **Command and transfer rings** are produced by the driver. The last
+//!   TRB of the segment is a link back to the start with Toggle Cycle
+//!   set, so the producer flips its cycle state on every pass.
+//! - **The event ring** is produced by the controller. It is described
+//!   by a segment table, here of one segment, and the driver reports its
+//!   progress through the interrupter's dequeue pointer.
+//!
+//! Commands are issued one at a time and each transfer ring has at most
+//! one transfer descriptor in flight, so producer rings are never full.
+
+use core::ptr;
+use core::sync::atomic::{fence, Ordering};
+
+use libddk::{Bti, DmaBuffer};
+use libsys::Result;
+
+/// TRB types
+pub mod trb_type {
+    pub const NORMAL: u32 = 1;
+    pub const SETUP: u32 = 2;
+    pub const DATA: u32 = 3;
+    pub const STATUS: u32 = 4;
+    pub const LINK: u32 = 6;
+    pub const ENABLE_SLOT: u32 = 9;
+    pub const DISABLE_SLOT: u32 = 10;
+    pub const ADDRESS_DEVICE: u32 = 11;
+    pub const CONFIGURE_ENDPOINT: u32 = 12;
+    pub const EVALUATE_CONTEXT: u32 = 13;
+    pub const RESET_ENDPOINT: u32 = 14;
+    pub const SET_TR_DEQUEUE: u32 = 16;
+    pub const TRANSFER_EVENT: u32 = 32;
+    pub const COMMAND_COMPLETION: u32 = 33;
+    pub const PORT_STATUS_CHANGE: u32 = 34;
+}
+
+/// Control field bits
+pub mod trb_flags {
+    pub const CYCLE: u32 = 1 << 0;
+
+    /// Link TRBs: flip the cycle state when following the link
+    pub const TOGGLE_CYCLE: u32 = 1 << 1;
+
+    /// Interrupt on short packet
+    pub const ISP: u32 = 1 << 2;
+
+    /// Interrupt on completion
+    pub const IOC: u32 = 1 << 5;
+
+    /// Immediate data: the parameter field holds the data itself
+    pub const IDT: u32 = 1 << 6;
+
+    /// Data and status stages: data flows to the host
+    pub const DIR_IN: u32 = 1 << 16;
+}
+
+/// Completion codes
+pub mod completion {
+    pub const SUCCESS: u8 = 1;
+    pub const USB_TRANSACTION_ERROR: u8 = 4;
+    pub const STALL: u8 = 6;
+    pub const SHORT_PACKET: u8 = 13;
+}
+
+/// Transfer request block
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Trb {
+    pub parameter: u64,
+    pub status: u32,
+    pub control: u32,
+}
+
+impl Trb {
+    /// A TRB of type `kind` with the remaining control bits in `control`
+    pub fn new(kind: u32, parameter: u64, status: u32, control: u32) -> Self {
+        Self {
+            parameter,
+            status,
+            control: kind << 10 | control,
+        }
+    }
+
+    /// `trb_type` value
+    pub fn trb_type(&self) -> u32 {
+        (self.control >> 10) & 0x3F
+    }
+
+    pub fn cycle(&self) -> bool {
+        self.control & trb_flags::CYCLE != 0
+    }
+
+    /// Events: `completion` code
+    pub fn completion_code(&self) -> u8 {
+        (self.status >> 24) as u8
+    }
+
+    /// Events and device commands: the slot concerned
+    pub fn slot_id(&self) -> u8 {
+        (self.control >> 24) as u8
+    }
+
+    /// Transfer events: the endpoint's device context index
+    pub fn endpoint_id(&self) -> u8 {
+        ((self.control >> 16) & 0x1F) as u8
+    }
+}
+
+/// TRBs per ring, filling one page
+pub const RING_SIZE: usize = 256;
+
+/// A ring produced by the driver
+pub struct Ring {
+    trbs: *mut Trb,
+    phys: u64,
+
+    /// TRBs including the link
+    size: usize,
+
+    enqueue: usize,
+    cycle: bool,
+
+    _buffer: Option<DmaBuffer>,
+}
+
+impl Ring {
+    /// Allocate an empty ring
+    pub fn new(bti: &Bti) -> Result<Self> {
+        let mut buffer = DmaBuffer::create(bti, RING_SIZE * core::mem::size_of::<Trb>())?;
+        buffer.as_mut_slice().fill(0);
+        let mut ring = unsafe { Self::from_raw(buffer.as_ptr() as *mut Trb, buffer.phys(), RING_SIZE) };
+        ring._buffer = Some(buffer);
+        Ok(ring)
+    }
+
+    /// Build a ring over zeroed memory at `trbs`, which the controller
+    /// sees at `phys`
+    ///
+    /// # Safety
+    ///
+    /// `trbs` must point to `size` TRBs that outlive the ring.
+    pub unsafe fn from_raw(trbs: *mut Trb, phys: u64, size: usize) -> Self {
+        let link = Trb::new(trb_type::LINK, phys, 0, trb_flags::TOGGLE_CYCLE);
+        ptr::write_volatile(trbs.add(size - 1), link);
+        Self {
+            trbs,
+            phys,
+            size,
+            enqueue: 0,
+            cycle: true,
+            _buffer: None,
+        }
+    }
+
+    /// Device address of the first TRB
+    pub fn phys(&self) -> u64 {
+        self.phys
+    }
+
+    /// Device address of the next TRB to be written, with the producer
+    /// cycle state in bit 0 as dequeue pointers take it
+    pub fn dequeue_pointer(&self) -> u64 {
+        (self.phys + (self.enqueue * core::mem::size_of::<Trb>()) as u64) | self.cycle as u64
+    }
+
+    /// Hand `trb` to the controller
+    ///
+    /// Returns the device address it was written at, which events
+    /// completing it carry.
+    pub fn push(&mut self, trb: Trb) -> u64 {
+        let addr = self.phys + (self.enqueue * core::mem::size_of::<Trb>()) as u64;
+        let cycle = self.cycle as u32;
+        unsafe {
+            let slot = self.trbs.add(self.enqueue);
+            ptr::write_volatile(&mut (*slot).parameter, trb.parameter);
+            ptr::write_volatile(&mut (*slot).status, trb.status);
+            // The cycle bit passes ownership, so it goes last
+            fence(Ordering::Release);
+            ptr::write_volatile(&mut (*slot).control, (trb.control & !trb_flags::CYCLE) | cycle);
+        }
+
+        self.enqueue += 1;
+        if self.enqueue == self.size - 1 {
+            unsafe {
+                let link = self.trbs.add(self.enqueue);
+                let control = ptr::read_volatile(&(*link).control);
+                fence(Ordering::Release);
+                ptr::write_volatile(&mut (*link).control, (control & !trb_flags::CYCLE) | cycle);
+            }
+            self.enqueue = 0;
+            self.cycle = !self.cycle;
+        }
+        addr
+    }
+}
+
+/// Event ring segment table entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SegmentEntry {
+    base: u64,
+    size: u32,
+    reserved: u32,
+}
+
+/// The ring the controller reports events on
+pub struct EventRing {
+    trbs: *const Trb,
+    phys: u64,
+    size: usize,
+    dequeue: usize,
+    cycle: bool,
+
+    /// Segment table address
+    table: u64,
+
+    _buffer: Option<DmaBuffer>,
+}
+
+impl EventRing {
+    /// Allocate a ring of one segment, followed by its segment table
+    pub fn new(bti: &Bti) -> Result<Self> {
+        let segment_bytes = RING_SIZE * core::mem::size_of::<Trb>();
+        let mut buffer = DmaBuffer::create(bti, segment_bytes + core::mem::size_of::<SegmentEntry>())?;
+        buffer.as_mut_slice().fill(0);
+
+        let entry = SegmentEntry {
+            base: buffer.phys(),
+            size: RING_SIZE as u32,
+            reserved: 0,
+        };
+        unsafe { ptr::write_volatile(buffer.as_ptr().add(segment_bytes) as *mut SegmentEntry, entry) };
+
+        let mut ring = unsafe { Self::from_raw(buffer.as_ptr() as *const Trb, buffer.phys(), RING_SIZE) };
+        ring.table = buffer.phys() + segment_bytes as u64;
+        ring._buffer = Some(buffer);
+        Ok(ring)
+    }
+
+    /// Build a ring over zeroed memory at `trbs`, which the controller
+    /// sees at `phys`
+    ///
+    /// # Safety
+    ///
+    /// `trbs` must point to `size` TRBs that outlive the ring.
+    pub unsafe fn from_raw(trbs: *const Trb, phys: u64, size: usize) -> Self {
+        Self {
+            trbs,
+            phys,
+            size,
+            dequeue: 0,
+            cycle: true,
+            table: 0,
+            _buffer: None,
+        }
+    }
+
+    /// Device address of the segment table
+    pub fn table(&self) -> u64 {
+        self.table
+    }
+
+    /// Number of segment table entries
+    pub fn table_size(&self) -> u32 {
+        1
+    }
+
+    /// Device address of the next TRB to be consumed
+    pub fn dequeue_pointer(&self) -> u64 {
+        self.phys + (self.dequeue * core::mem::size_of::<Trb>()) as u64
+    }
+
+    /// Take the next event, if the controller has written one
+    pub fn pop(&mut self) -> Option<Trb> {
+        let trb = unsafe { ptr::read_volatile(self.trbs.add(self.dequeue)) };
+        if trb.cycle() != self.cycle {
+            return None;
+        }
+        fence(Ordering::Acquire);
+
+        self.dequeue += 1;
+        if self.dequeue == self.size {
+            self.dequeue = 0;
+            self.cycle = !self.cycle;
+        }
+        Some(trb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trb_fields() {
+        let trb = Trb {
+            parameter: 0x1000,
+            status: 13 << 24 | 5,
+            control: 3 << 24 | 3 << 16 | trb_type::TRANSFER_EVENT << 10 | 1,
+        };
+        assert_eq!(trb.trb_type(), trb_type::TRANSFER_EVENT);
+        assert_eq!(trb.completion_code(), completion::SHORT_PACKET);
+        assert_eq!(trb.slot_id(), 3);
+        assert_eq!(trb.endpoint_id(), 3);
+        assert!(trb.cycle());
+        assert_eq!(core::mem::size_of::<Trb>(), 16);
+    }
+
+    #[test]
+    fn test_ring_wraps_through_link() {
+        let mut memory = [Trb::default(); 4];
+        let mut ring = unsafe { Ring::from_raw(memory.as_mut_ptr(), 0x10_0000, 4) };
+        let noop = Trb::new(trb_type::NORMAL, 0, 0, 0);
+
+        assert_eq!(ring.push(noop), 0x10_0000);
+        assert_eq!(ring.push(noop), 0x10_0010);
+        assert_eq!(ring.dequeue_pointer(), 0x10_0021);
+        assert_eq!(ring.push(noop), 0x10_0020);
+
+        // The link now belongs to the controller and the next pass runs
+        // with the cycle bit clear
+        assert_eq!(memory[3].trb_type(), trb_type::LINK);
+        assert_eq!(memory[3].parameter, 0x10_0000);
+        assert!(memory[3].cycle());
+        assert_eq!(ring.dequeue_pointer(), 0x10_0000);
+        assert_eq!(ring.push(noop), 0x10_0000);
+        assert!(!memory[0].cycle());
+        assert!(memory[1].cycle());
+    }
+
+    #[test]
+    fn test_event_ring_follows_cycle() {
+        let mut memory = [Trb::default(); 2];
+        let trbs = memory.as_mut_ptr();
+        let mut ring = unsafe { EventRing::from_raw(trbs, 0x2000, 2) };
+        assert_eq!(ring.pop(), None);
+
+        // The controller writes events behind the driver's back
+        unsafe {
+            *trbs = Trb::new(trb_type::COMMAND_COMPLETION, 0, 0, trb_flags::CYCLE);
+            *trbs.add(1) = Trb::new(trb_type::PORT_STATUS_CHANGE, 0, 0, trb_flags::CYCLE);
+        }
+        assert_eq!(ring.pop().map(|trb| trb.trb_type()), Some(trb_type::COMMAND_COMPLETION));
+        assert_eq!(ring.pop().map(|trb| trb.trb_type()), Some(trb_type::PORT_STATUS_CHANGE));
+        assert_eq!(ring.dequeue_pointer(), 0x2000);
+
+        // Old events stay behind until rewritten with the new cycle
+        assert_eq!(ring.pop(), None);
+        unsafe { (*trbs).control &= !trb_flags::CYCLE };
+        assert!(ring.pop().is_some());
+    }
+}
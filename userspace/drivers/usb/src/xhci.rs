@@ -0,0 +1,1022 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! xHCI Host Controller Driver
+//!
+//! Brings up an xHCI controller and enumerates the devices on its root
+//! ports:
+//!
+//! 1. Claim the controller from the firmware, reset it and hand it the
+//!    device context array, scratchpad buffers, command ring and event
+//!    ring.
+//! 2. For each connected port, reset the port, enable a slot and address
+//!    the device, then read its device and configuration descriptors
+//!    over endpoint 0.
+//! 3. Boot keyboards get their interrupt endpoint configured, are
+//!    switched to the boot protocol and always have one report transfer
+//!    queued. Reports become input events published as instances of the
+//!    DDK input class, numbered from 1 in order of attachment.
+//!
+//! Other devices are addressed and left unconfigured. Hubs are not
+//! supported, so only devices plugged straight into the root ports are
+//! found. There is no blocking wait on the event ring yet: the driver
+//! host polls the controller, which also handles hot-plugging through
+//! port status change events.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use libddk::input::input_kind;
+use libddk::protocol::bus_protocol;
+use libddk::*;
+use libsys::{clock, Error, Handle, Result, Status};
+
+use crate::descriptor::{
+    class, descriptor_type, hid_request, transfer_type, Configuration, DeviceInfo, Endpoint, SetupPacket,
+};
+use crate::hid::{self, BootKeyboard};
+use crate::ring::{completion, trb_flags, trb_type, EventRing, Ring, Trb};
+
+/// PCI class of xHCI controllers
+const PCI_CLASS_SERIAL_BUS: u8 = 0x0C;
+const PCI_SUBCLASS_USB: u8 = 0x03;
+const PCI_PROG_IF_XHCI: u8 = 0x30;
+
+/// Capability registers
+mod cap {
+    pub const CAPLENGTH: usize = 0x00;
+    pub const HCSPARAMS1: usize = 0x04;
+    pub const HCSPARAMS2: usize = 0x08;
+    pub const HCCPARAMS1: usize = 0x10;
+    pub const DBOFF: usize = 0x14;
+    pub const RTSOFF: usize = 0x18;
+}
+
+/// Operational registers
+mod op {
+    pub const USBCMD: usize = 0x00;
+    pub const USBSTS: usize = 0x04;
+    pub const CRCR: usize = 0x18;
+    pub const DCBAAP: usize = 0x30;
+    pub const CONFIG: usize = 0x38;
+
+    /// PORTSC of port 1; each port has 16 bytes of registers
+    pub const PORTSC: usize = 0x400;
+    pub const PORT_STRIDE: usize = 0x10;
+}
+
+/// Interrupter 0 registers, relative to the runtime registers
+mod intr {
+    pub const ERSTSZ: usize = 0x28;
+    pub const ERSTBA: usize = 0x30;
+    pub const ERDP: usize = 0x38;
+}
+
+mod usbcmd {
+    pub const RUN: u32 = 1 << 0;
+    pub const RESET: u32 = 1 << 1;
+}
+
+mod usbsts {
+    pub const HALTED: u32 = 1 << 0;
+    pub const NOT_READY: u32 = 1 << 11;
+}
+
+mod portsc {
+    /// Current connect status
+    pub const CCS: u32 = 1 << 0;
+
+    /// Port enabled; writing 1 disables the port
+    pub const PED: u32 = 1 << 1;
+
+    /// Port reset
+    pub const PR: u32 = 1 << 4;
+
+    pub const SPEED_SHIFT: u32 = 10;
+    pub const SPEED_MASK: u32 = 0xF;
+
+    /// Reset change
+    pub const PRC: u32 = 1 << 21;
+
+    /// Change bits, cleared by writing 1
+    pub const CHANGES: u32 = 0x7F << 17;
+}
+
+/// Event handler busy, cleared by writing 1 to ERDP
+const ERDP_EHB: u64 = 1 << 3;
+
+/// USB legacy support capability, through which the firmware gives the
+/// controller up
+mod legacy {
+    pub const CAP_ID: u32 = 1;
+    pub const BIOS_OWNED: u32 = 1 << 16;
+    pub const OS_OWNED: u32 = 1 << 24;
+
+    /// USBLEGCTLSTS: SMI enables, and SMI events cleared by writing 1
+    pub const CTLSTS: usize = 0x04;
+    pub const SMI_ENABLES: u32 = 0x0000_E011;
+    pub const SMI_EVENTS: u32 = 0xE000_0000;
+}
+
+/// Port speeds
+pub mod speed {
+    pub const FULL: u8 = 1;
+    pub const LOW: u8 = 2;
+    pub const HIGH: u8 = 3;
+    pub const SUPER: u8 = 4;
+}
+
+/// Endpoint context types
+mod ep_type {
+    pub const CONTROL: u32 = 4;
+    pub const INTERRUPT_IN: u32 = 7;
+}
+
+/// Slots the driver enables at most
+const MAX_SLOTS: u8 = 32;
+
+/// How long the controller gets to finish a reset, command or transfer
+const TIMEOUT_NS: i64 = 1_000_000_000;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Layout of a device's I/O page: the keyboard report, then the data
+/// stage of control transfers
+const REPORT_OFFSET: usize = 0;
+const CONTROL_OFFSET: usize = 512;
+const CONTROL_BUFFER_SIZE: usize = PAGE_SIZE - CONTROL_OFFSET;
+
+/// Largest endpoint 0 packet before the device descriptor says otherwise
+fn default_max_packet0(speed: u8) -> u16 {
+    match speed {
+        speed::HIGH => 64,
+        speed::SUPER => 512,
+        _ => 8,
+    }
+}
+
+/// Endpoint 0 packet size from `bMaxPacketSize0`
+fn max_packet0(speed: u8, value: u8) -> u16 {
+    if speed >= speed::SUPER {
+        1 << value.min(15)
+    } else {
+        value as u16
+    }
+}
+
+/// Endpoint context interval, in 2^n 125 us units, for an interrupt
+/// endpoint's `bInterval`
+fn interrupt_interval(speed: u8, interval: u8) -> u32 {
+    match speed {
+        // Frames of 1 ms
+        speed::FULL | speed::LOW => {
+            let microframes = (interval.max(1) as u32) * 8;
+            (31 - microframes.leading_zeros()).clamp(3, 10)
+        }
+        // Already an exponent, plus one
+        _ => (interval.clamp(1, 16) - 1) as u32,
+    }
+}
+
+/// Device context index of an endpoint
+fn endpoint_index(endpoint: &Endpoint) -> u8 {
+    endpoint.number() * 2 + endpoint.is_in() as u8
+}
+
+/// Builds input contexts for Address Device, Evaluate Context and
+/// Configure Endpoint
+///
+/// Entry 0 is the input control context, entry 1 the slot context and
+/// entry 1 + n the context of endpoint index n.
+struct InputContext<'a> {
+    buf: &'a mut [u8],
+    context_size: usize,
+}
+
+impl<'a> InputContext<'a> {
+    /// Entries in an input context
+    const ENTRIES: usize = 33;
+
+    fn new(buf: &'a mut [u8], context_size: usize) -> Self {
+        buf[..Self::ENTRIES * context_size].fill(0);
+        Self { buf, context_size }
+    }
+
+    fn set(&mut self, entry: usize, dword: usize, value: u32) {
+        let offset = entry * self.context_size + dword * 4;
+        self.buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Mark contexts to evaluate: bit 0 is the slot, bit n endpoint n
+    fn add(&mut self, flags: u32) {
+        self.set(0, 1, flags);
+    }
+
+    /// Slot context of a root port device whose highest endpoint index
+    /// is `last_endpoint`
+    fn slot(&mut self, speed: u8, port: u8, last_endpoint: u8) {
+        self.set(1, 0, (last_endpoint as u32) << 27 | (speed as u32) << 20);
+        self.set(1, 1, (port as u32) << 16);
+    }
+
+    fn endpoint(&mut self, index: u8, kind: u32, max_packet: u16, interval: u32, ring: &Ring) {
+        let entry = 1 + index as usize;
+        let dequeue = ring.dequeue_pointer();
+        self.set(entry, 0, interval << 16);
+        // Three retries on errors
+        self.set(entry, 1, (max_packet as u32) << 16 | kind << 3 | 3 << 1);
+        self.set(entry, 2, dequeue as u32);
+        self.set(entry, 3, (dequeue >> 32) as u32);
+        let average = if kind == ep_type::CONTROL { 8 } else { max_packet as u32 };
+        self.set(entry, 4, (max_packet as u32) << 16 | average);
+    }
+}
+
+/// Events of one keyboard, waiting for the input server
+struct KeyQueue {
+    info: InputInfo,
+    events: VecDeque<KeyEvent>,
+}
+
+impl InputDevice for KeyQueue {
+    fn info(&self) -> InputInfo {
+        self.info
+    }
+
+    fn read_event(&mut self) -> Result<Option<KeyEvent>> {
+        Ok(self.events.pop_front())
+    }
+}
+
+/// A boot keyboard
+struct Keyboard {
+    /// Input class instance
+    instance: u32,
+
+    interface: u8,
+
+    /// Device context index of the interrupt endpoint
+    endpoint: u8,
+    ring: Ring,
+
+    state: BootKeyboard,
+    queue: KeyQueue,
+    server: Option<InputServer>,
+}
+
+/// A device on a root port
+struct UsbDevice {
+    port: u8,
+    slot: u8,
+    speed: u8,
+    info: DeviceInfo,
+
+    /// Output device context, written by the controller
+    _context: DmaBuffer,
+
+    /// Default control endpoint
+    ep0: Ring,
+
+    io: DmaBuffer,
+    keyboard: Option<Keyboard>,
+}
+
+/// An xHCI controller
+pub struct Xhci {
+    regs: MmioBuffer,
+
+    /// Offsets of the operational and runtime registers and the doorbells
+    op: usize,
+    runtime: usize,
+    doorbells: usize,
+
+    max_ports: u8,
+
+    /// Size of one device context entry: 32 or 64 bytes
+    context_size: usize,
+
+    bti: Bti,
+    dcbaa: DmaBuffer,
+    _scratchpads: Option<(DmaBuffer, DmaBuffer)>,
+    commands: Ring,
+    events: EventRing,
+
+    /// Events that arrived while waiting for another
+    deferred: VecDeque<Trb>,
+
+    /// Input context scratch page
+    input: DmaBuffer,
+
+    devices: Vec<UsbDevice>,
+    next_instance: u32,
+}
+
+impl Xhci {
+    /// Take over and start the controller behind `device`
+    pub fn new(device: &PciDevice) -> Result<Self> {
+        let regs = device.map_bar(0)?;
+        device.enable_bus_master(true)?;
+
+        let op = regs.read8(cap::CAPLENGTH) as usize;
+        let hcsparams1 = regs.read32(cap::HCSPARAMS1);
+        let hcsparams2 = regs.read32(cap::HCSPARAMS2);
+        let hccparams1 = regs.read32(cap::HCCPARAMS1);
+        let runtime = (regs.read32(cap::RTSOFF) & !0x1F) as usize;
+        let doorbells = (regs.read32(cap::DBOFF) & !0x3) as usize;
+        let max_slots = (hcsparams1 as u8).min(MAX_SLOTS);
+        let max_ports = (hcsparams1 >> 24) as u8;
+        let context_size = if hccparams1 & (1 << 2) != 0 { 64 } else { 32 };
+
+        take_ownership(&regs, ((hccparams1 >> 16) as usize) * 4)?;
+
+        // Stop and reset
+        regs.write32(op + op::USBCMD, regs.read32(op + op::USBCMD) & !usbcmd::RUN);
+        wait_for(&regs, op + op::USBSTS, usbsts::HALTED, usbsts::HALTED)?;
+        regs.write32(op + op::USBCMD, usbcmd::RESET);
+        wait_for(&regs, op + op::USBCMD, usbcmd::RESET, 0)?;
+        wait_for(&regs, op + op::USBSTS, usbsts::NOT_READY, 0)?;
+
+        let bti = Bti::create(0)?;
+        let mut dcbaa = DmaBuffer::create(&bti, (MAX_SLOTS as usize + 1) * 8)?;
+        dcbaa.as_mut_slice().fill(0);
+
+        // Scratchpad count: high bits in 25:21, low bits in 31:27
+        let scratchpad_count = ((hcsparams2 >> 21 & 0x1F) << 5 | hcsparams2 >> 27) as usize;
+        let scratchpads = if scratchpad_count > 0 {
+            let mut array = DmaBuffer::create(&bti, scratchpad_count * 8)?;
+            let mut pages = DmaBuffer::create(&bti, scratchpad_count * PAGE_SIZE)?;
+            pages.as_mut_slice().fill(0);
+            for (i, entry) in array.as_mut_slice().chunks_exact_mut(8).take(scratchpad_count).enumerate() {
+                entry.copy_from_slice(&(pages.phys() + (i * PAGE_SIZE) as u64).to_le_bytes());
+            }
+            dcbaa.as_mut_slice()[0..8].copy_from_slice(&array.phys().to_le_bytes());
+            Some((array, pages))
+        } else {
+            None
+        };
+
+        let commands = Ring::new(&bti)?;
+        let events = EventRing::new(&bti)?;
+        let input = DmaBuffer::create(&bti, PAGE_SIZE)?;
+
+        regs.write32(op + op::CONFIG, max_slots as u32);
+        write64(&regs, op + op::DCBAAP, dcbaa.phys());
+        write64(&regs, op + op::CRCR, commands.dequeue_pointer());
+
+        // Events are polled, so the interrupter stays disabled
+        regs.write32(runtime + intr::ERSTSZ, events.table_size());
+        write64(&regs, runtime + intr::ERDP, events.dequeue_pointer());
+        write64(&regs, runtime + intr::ERSTBA, events.table());
+
+        regs.write32(op + op::USBCMD, usbcmd::RUN);
+        wait_for(&regs, op + op::USBSTS, usbsts::HALTED, 0)?;
+
+        Ok(Self {
+            regs,
+            op,
+            runtime,
+            doorbells,
+            max_ports,
+            context_size,
+            bti,
+            dcbaa,
+            _scratchpads: scratchpads,
+            commands,
+            events,
+            deferred: VecDeque::new(),
+            input,
+            devices: Vec::new(),
+            next_instance: 1,
+        })
+    }
+
+    /// Halt the controller
+    pub fn stop(&mut self) -> Result<()> {
+        let cmd = self.regs.read32(self.op + op::USBCMD);
+        self.regs.write32(self.op + op::USBCMD, cmd & !usbcmd::RUN);
+        wait_for(&self.regs, self.op + op::USBSTS, usbsts::HALTED, usbsts::HALTED)
+    }
+
+    /// Enumerate devices on every connected root port
+    pub fn scan_ports(&mut self) {
+        for port in 1..=self.max_ports {
+            // A device that fails to enumerate doesn't stop the others
+            let _ = self.port_changed(port);
+        }
+    }
+
+    /// Number of devices found
+    pub fn device_count(&self) -> usize {
+        self.devices.len()
+    }
+
+    fn portsc(&self, port: u8) -> usize {
+        self.op + op::PORTSC + (port as usize - 1) * op::PORT_STRIDE
+    }
+
+    /// Attach or detach the device on `port` to match its connect status
+    fn port_changed(&mut self, port: u8) -> Result<()> {
+        let reg = self.portsc(port);
+        let status = self.regs.read32(reg);
+        // Writing back a 1 to PED would disable the port
+        self.regs.write32(reg, status & !portsc::PED);
+
+        let existing = self.devices.iter().position(|dev| dev.port == port);
+        match (status & portsc::CCS != 0, existing) {
+            (true, None) => self.attach(port),
+            (false, Some(index)) => self.detach(index),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reset `port` and enumerate its device
+    fn attach(&mut self, port: u8) -> Result<()> {
+        let reg = self.portsc(port);
+        // USB 3 ports enable themselves once the link is trained
+        if self.regs.read32(reg) & portsc::PED == 0 {
+            let status = self.regs.read32(reg) & !(portsc::PED | portsc::CHANGES);
+            self.regs.write32(reg, status | portsc::PR);
+            wait_for(&self.regs, reg, portsc::PRC, portsc::PRC)?;
+            let status = self.regs.read32(reg);
+            self.regs.write32(reg, (status & !(portsc::PED | portsc::CHANGES)) | portsc::PRC);
+            if status & portsc::PED == 0 {
+                return Err(Error::new(Status::IoError));
+            }
+        }
+        let speed = ((self.regs.read32(reg) >> portsc::SPEED_SHIFT) & portsc::SPEED_MASK) as u8;
+
+        let slot = self.command(Trb::new(trb_type::ENABLE_SLOT, 0, 0, 0))?.slot_id();
+        let result = self.address(port, slot, speed).and_then(|()| self.enumerate(self.devices.len() - 1));
+        if result.is_err() {
+            if let Some(index) = self.devices.iter().position(|dev| dev.slot == slot) {
+                self.devices.remove(index);
+            }
+            let _ = self.command(Trb::new(trb_type::DISABLE_SLOT, 0, 0, (slot as u32) << 24));
+            self.set_device_context(slot, 0);
+        }
+        result
+    }
+
+    /// Forget the device at `index` and free its slot
+    fn detach(&mut self, index: usize) -> Result<()> {
+        let device = self.devices.remove(index);
+        self.command(Trb::new(trb_type::DISABLE_SLOT, 0, 0, (device.slot as u32) << 24))?;
+        self.set_device_context(device.slot, 0);
+        Ok(())
+    }
+
+    fn set_device_context(&mut self, slot: u8, phys: u64) {
+        let offset = slot as usize * 8;
+        self.dcbaa.as_mut_slice()[offset..offset + 8].copy_from_slice(&phys.to_le_bytes());
+    }
+
+    /// Give the device on `port` address `slot`, leaving it last in the
+    /// device list
+    fn address(&mut self, port: u8, slot: u8, speed: u8) -> Result<()> {
+        let mut context = DmaBuffer::create(&self.bti, PAGE_SIZE)?;
+        context.as_mut_slice().fill(0);
+        let ep0 = Ring::new(&self.bti)?;
+        let io = DmaBuffer::create(&self.bti, PAGE_SIZE)?;
+        self.set_device_context(slot, context.phys());
+
+        let mut input = InputContext::new(self.input.as_mut_slice(), self.context_size);
+        input.add(0b11);
+        input.slot(speed, port, 1);
+        input.endpoint(1, ep_type::CONTROL, default_max_packet0(speed), 0, &ep0);
+        let input_phys = self.input.phys();
+        self.command(Trb::new(trb_type::ADDRESS_DEVICE, input_phys, 0, (slot as u32) << 24))?;
+
+        self.devices.push(UsbDevice {
+            port,
+            slot,
+            speed,
+            info: DeviceInfo::default(),
+            _context: context,
+            ep0,
+            io,
+            keyboard: None,
+        });
+        Ok(())
+    }
+
+    /// Read the descriptors of the device at `index` and start its driver
+    fn enumerate(&mut self, index: usize) -> Result<()> {
+        let (slot, speed) = (self.devices[index].slot, self.devices[index].speed);
+
+        // Full-speed devices may use larger packets on endpoint 0 than
+        // the 8 bytes assumed so far
+        let head = self.control_in(index, SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, 8))?;
+        let head = DeviceInfo::parse(&head).ok_or(Error::new(Status::IoError))?;
+        let max_packet = max_packet0(speed, head.max_packet_size0);
+        if max_packet != default_max_packet0(speed) {
+            let mut input = InputContext::new(self.input.as_mut_slice(), self.context_size);
+            input.add(0b10);
+            input.endpoint(1, ep_type::CONTROL, max_packet, 0, &self.devices[index].ep0);
+            let input_phys = self.input.phys();
+            self.command(Trb::new(trb_type::EVALUATE_CONTEXT, input_phys, 0, (slot as u32) << 24))?;
+        }
+
+        let desc = self.control_in(
+            index,
+            SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, DeviceInfo::SIZE as u16),
+        )?;
+        self.devices[index].info = DeviceInfo::parse(&desc).ok_or(Error::new(Status::IoError))?;
+
+        let header = self.control_in(
+            index,
+            SetupPacket::get_descriptor(descriptor_type::CONFIGURATION, 0, Configuration::HEADER_SIZE as u16),
+        )?;
+        let total = Configuration::total_length(&header).ok_or(Error::new(Status::IoError))?;
+        let total = (total as usize).min(CONTROL_BUFFER_SIZE) as u16;
+        let desc = self.control_in(index, SetupPacket::get_descriptor(descriptor_type::CONFIGURATION, 0, total))?;
+        let config = Configuration::parse(&desc).ok_or(Error::new(Status::IoError))?;
+
+        let keyboard = config.interfaces.iter().find_map(|interface| {
+            let boot_keyboard = interface.class == class::HID
+                && interface.subclass == hid::SUBCLASS_BOOT
+                && interface.protocol == hid::PROTOCOL_KEYBOARD;
+            let endpoint = interface
+                .endpoints
+                .iter()
+                .find(|ep| ep.is_in() && ep.transfer_type() == transfer_type::INTERRUPT)?;
+            boot_keyboard.then_some((interface.number, *endpoint))
+        });
+        match keyboard {
+            Some((interface, endpoint)) => self.start_keyboard(index, config.value, interface, &endpoint),
+            None => Ok(()),
+        }
+    }
+
+    /// Configure a boot keyboard and queue its first report
+    fn start_keyboard(&mut self, index: usize, config: u8, interface: u8, endpoint: &Endpoint) -> Result<()> {
+        let (port, slot, speed) = {
+            let dev = &self.devices[index];
+            (dev.port, dev.slot, dev.speed)
+        };
+        let dci = endpoint_index(endpoint);
+        let ring = Ring::new(&self.bti)?;
+
+        let mut input = InputContext::new(self.input.as_mut_slice(), self.context_size);
+        input.add(1 | 1 << dci);
+        input.slot(speed, port, dci);
+        input.endpoint(
+            dci,
+            ep_type::INTERRUPT_IN,
+            endpoint.max_packet_size & 0x7FF,
+            interrupt_interval(speed, endpoint.interval),
+            &ring,
+        );
+        let input_phys = self.input.phys();
+        self.command(Trb::new(trb_type::CONFIGURE_ENDPOINT, input_phys, 0, (slot as u32) << 24))?;
+
+        self.control_out(index, SetupPacket::set_configuration(config), &[])?;
+        self.control_out(
+            index,
+            SetupPacket::hid(hid_request::SET_PROTOCOL, hid::BOOT_PROTOCOL, interface, 0),
+            &[],
+        )?;
+        // Report only on change; some keyboards refuse, which is harmless
+        let _ = self.control_out(index, SetupPacket::hid(hid_request::SET_IDLE, 0, interface, 0), &[]);
+
+        let info = self.devices[index].info;
+        self.devices[index].keyboard = Some(Keyboard {
+            instance: self.next_instance,
+            interface,
+            endpoint: dci,
+            ring,
+            state: BootKeyboard::new(),
+            queue: KeyQueue {
+                info: InputInfo {
+                    kind: input_kind::KEYBOARD,
+                    vendor_id: info.vendor_id,
+                    product_id: info.product_id,
+                },
+                events: VecDeque::new(),
+            },
+            server: None,
+        });
+        self.next_instance += 1;
+        self.queue_report(index);
+        Ok(())
+    }
+
+    /// Queue a transfer for the next report of the keyboard at `index`
+    fn queue_report(&mut self, index: usize) {
+        let dev = &mut self.devices[index];
+        let Some(keyboard) = dev.keyboard.as_mut() else {
+            return;
+        };
+        let trb = Trb::new(
+            trb_type::NORMAL,
+            dev.io.phys() + REPORT_OFFSET as u64,
+            hid::REPORT_SIZE as u32,
+            trb_flags::IOC | trb_flags::ISP,
+        );
+        keyboard.ring.push(trb);
+        let (slot, endpoint) = (dev.slot, keyboard.endpoint);
+        self.ring_doorbell(slot, endpoint);
+    }
+
+    fn ring_doorbell(&self, slot: u8, target: u8) {
+        self.regs.write32(self.doorbells + slot as usize * 4, target as u32);
+    }
+
+    /// Take the next event off the ring
+    fn next_event(&mut self) -> Option<Trb> {
+        let event = self.events.pop()?;
+        write64(&self.regs, self.runtime + intr::ERDP, self.events.dequeue_pointer() | ERDP_EHB);
+        Some(event)
+    }
+
+    /// Wait for an event accepted by `wanted`, keeping the others for
+    /// `poll`
+    fn wait_event(&mut self, wanted: impl Fn(&Trb) -> bool) -> Result<Trb> {
+        let deadline = clock::monotonic() + TIMEOUT_NS;
+        loop {
+            match self.next_event() {
+                Some(event) if wanted(&event) => return Ok(event),
+                Some(event) => self.deferred.push_back(event),
+                None if clock::monotonic() > deadline => return Err(Error::new(Status::TimedOut)),
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+
+    /// Run a command and wait for it to complete
+    fn command(&mut self, trb: Trb) -> Result<Trb> {
+        let addr = self.commands.push(trb);
+        self.ring_doorbell(0, 0);
+        let event = self.wait_event(|event| {
+            event.trb_type() == trb_type::COMMAND_COMPLETION && event.parameter == addr
+        })?;
+        completion_result(event.completion_code())?;
+        Ok(event)
+    }
+
+    /// Run a control transfer on the default endpoint of the device at
+    /// `index`, with the data stage in the control buffer
+    fn control(&mut self, index: usize, setup: SetupPacket) -> Result<()> {
+        let length = setup.length as u32;
+        if length as usize > CONTROL_BUFFER_SIZE {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let dev = &mut self.devices[index];
+        let dir_in = if setup.is_in() { trb_flags::DIR_IN } else { 0 };
+
+        // Transfer type: 0 no data, 2 OUT, 3 IN
+        let transfer = match (length, setup.is_in()) {
+            (0, _) => 0,
+            (_, false) => 2,
+            (_, true) => 3,
+        };
+        dev.ep0.push(Trb::new(trb_type::SETUP, setup.to_u64(), 8, trb_flags::IDT | transfer << 16));
+        if length > 0 {
+            let data = dev.io.phys() + CONTROL_OFFSET as u64;
+            dev.ep0.push(Trb::new(trb_type::DATA, data, length, dir_in));
+        }
+        // The status stage runs opposite to the data
+        let status_dir = if length == 0 || !setup.is_in() { trb_flags::DIR_IN } else { 0 };
+        dev.ep0.push(Trb::new(trb_type::STATUS, 0, 0, trb_flags::IOC | status_dir));
+
+        let slot = dev.slot;
+        self.ring_doorbell(slot, 1);
+        let event = self.wait_event(|event| {
+            event.trb_type() == trb_type::TRANSFER_EVENT && event.slot_id() == slot && event.endpoint_id() == 1
+        })?;
+        let result = completion_result(event.completion_code());
+        if event.completion_code() == completion::STALL {
+            self.recover(index, 1)?;
+        }
+        result
+    }
+
+    /// Control transfer reading `setup.length` bytes
+    fn control_in(&mut self, index: usize, setup: SetupPacket) -> Result<Vec<u8>> {
+        self.devices[index].io.as_mut_slice()[CONTROL_OFFSET..].fill(0);
+        self.control(index, setup)?;
+        let len = setup.length as usize;
+        Ok(self.devices[index].io.as_slice()[CONTROL_OFFSET..CONTROL_OFFSET + len].to_vec())
+    }
+
+    /// Control transfer sending `data`
+    fn control_out(&mut self, index: usize, mut setup: SetupPacket, data: &[u8]) -> Result<()> {
+        if data.len() > CONTROL_BUFFER_SIZE {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        self.devices[index].io.as_mut_slice()[CONTROL_OFFSET..CONTROL_OFFSET + data.len()].copy_from_slice(data);
+        setup.length = data.len() as u16;
+        self.control(index, setup)
+    }
+
+    /// Restart a halted endpoint past the transfer that stalled it
+    fn recover(&mut self, index: usize, endpoint: u8) -> Result<()> {
+        let dev = &self.devices[index];
+        let slot = (dev.slot as u32) << 24;
+        let dequeue = match (endpoint, dev.keyboard.as_ref()) {
+            (1, _) => dev.ep0.dequeue_pointer(),
+            (_, Some(keyboard)) if keyboard.endpoint == endpoint => keyboard.ring.dequeue_pointer(),
+            _ => return Err(Error::new(Status::InvalidArgs)),
+        };
+        let target = slot | (endpoint as u32) << 16;
+        self.command(Trb::new(trb_type::RESET_ENDPOINT, 0, 0, target))?;
+        self.command(Trb::new(trb_type::SET_TR_DEQUEUE, dequeue, 0, target))?;
+        Ok(())
+    }
+
+    fn handle_event(&mut self, event: Trb) -> Result<()> {
+        match event.trb_type() {
+            trb_type::PORT_STATUS_CHANGE => {
+                let port = (event.parameter >> 24) as u8;
+                if (1..=self.max_ports).contains(&port) {
+                    let _ = self.port_changed(port);
+                }
+            }
+            trb_type::TRANSFER_EVENT => {
+                let Some(index) = self.devices.iter().position(|dev| dev.slot == event.slot_id()) else {
+                    return Ok(());
+                };
+                let is_report = match self.devices[index].keyboard.as_ref() {
+                    Some(keyboard) => keyboard.endpoint == event.endpoint_id(),
+                    None => false,
+                };
+                if is_report {
+                    self.report_done(index, event.completion_code())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Decode a completed report and queue the next
+    fn report_done(&mut self, index: usize, code: u8) -> Result<()> {
+        match code {
+            completion::SUCCESS | completion::SHORT_PACKET => {}
+            completion::STALL => {
+                let endpoint = self.devices[index].keyboard.as_ref().map_or(0, |keyboard| keyboard.endpoint);
+                self.recover(index, endpoint)?;
+                self.queue_report(index);
+                return Ok(());
+            }
+            // The device is likely gone; its port status change follows
+            _ => return Ok(()),
+        }
+
+        let dev = &mut self.devices[index];
+        let Some(keyboard) = dev.keyboard.as_mut() else {
+            return Ok(());
+        };
+        let report = &dev.io.as_slice()[REPORT_OFFSET..REPORT_OFFSET + hid::REPORT_SIZE];
+        let events = &mut keyboard.queue.events;
+        let leds_changed = keyboard.state.report(report, |event| events.push_back(event));
+        let (interface, leds) = (keyboard.interface, keyboard.state.leds());
+
+        self.queue_report(index);
+        if leds_changed {
+            let setup = SetupPacket::hid(hid_request::SET_REPORT, hid::LED_REPORT, interface, 0);
+            // Keys still work with stale LEDs
+            let _ = self.control_out(index, setup, &[leds]);
+        }
+        Ok(())
+    }
+
+    /// Connect a client to keyboard `instance`
+    pub fn open(&mut self, instance: u32) -> Result<Connection> {
+        let keyboard = self
+            .devices
+            .iter_mut()
+            .filter_map(|dev| dev.keyboard.as_mut())
+            .find(|keyboard| keyboard.instance == instance)
+            .ok_or(Error::new(Status::NotFound))?;
+        let (server, connection) = InputServer::create(&keyboard.queue.info)?;
+        keyboard.server = Some(server);
+        Ok(connection)
+    }
+
+    /// Handle pending events and pass key events on to clients
+    ///
+    /// Returns true if any work was done.
+    pub fn poll(&mut self) -> Result<bool> {
+        let mut progress = false;
+        loop {
+            let event = match self.deferred.pop_front() {
+                Some(event) => event,
+                None => match self.next_event() {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+            self.handle_event(event)?;
+            progress = true;
+        }
+
+        for keyboard in self.devices.iter_mut().filter_map(|dev| dev.keyboard.as_mut()) {
+            match keyboard.server.as_mut() {
+                Some(server) => progress |= server.poll(&mut keyboard.queue)?,
+                // Nobody is listening yet
+                None => keyboard.queue.events.clear(),
+            }
+        }
+        Ok(progress)
+    }
+}
+
+/// Write a 64-bit register as two 32-bit halves, low first, for
+/// controllers that only take 32-bit accesses
+fn write64(regs: &MmioBuffer, offset: usize, value: u64) {
+    regs.write32(offset, value as u32);
+    regs.write32(offset + 4, (value >> 32) as u32);
+}
+
+/// Wait until the bits `mask` of register `offset` read `value`
+fn wait_for(regs: &MmioBuffer, offset: usize, mask: u32, value: u32) -> Result<()> {
+    let deadline = clock::monotonic() + TIMEOUT_NS;
+    while regs.read32(offset) & mask != value {
+        if clock::monotonic() > deadline {
+            return Err(Error::new(Status::TimedOut));
+        }
+        core::hint::spin_loop();
+    }
+    Ok(())
+}
+
+/// Claim the controller from the firmware through the legacy support
+/// capability, if it has one
+///
+/// `offset` is the first extended capability; 0 means none.
+fn take_ownership(regs: &MmioBuffer, mut offset: usize) -> Result<()> {
+    while offset != 0 {
+        let header = regs.read32(offset);
+        if header & 0xFF == legacy::CAP_ID {
+            regs.write32(offset, header | legacy::OS_OWNED);
+            wait_for(regs, offset, legacy::BIOS_OWNED, 0)?;
+            // No more SMIs from the controller
+            let ctlsts = regs.read32(offset + legacy::CTLSTS);
+            regs.write32(
+                offset + legacy::CTLSTS,
+                (ctlsts & !legacy::SMI_ENABLES) | legacy::SMI_EVENTS,
+            );
+            return Ok(());
+        }
+        let next = ((header >> 8) & 0xFF) as usize;
+        if next == 0 {
+            break;
+        }
+        offset += next * 4;
+    }
+    Ok(())
+}
+
+/// Map a completion code to a result
+fn completion_result(code: u8) -> Result<()> {
+    match code {
+        completion::SUCCESS | completion::SHORT_PACKET => Ok(()),
+        completion::STALL => Err(Error::new(Status::NotSupported)),
+        _ => Err(Error::new(Status::IoError)),
+    }
+}
+
+/// ============================================================================
+/// Driver
+/// ============================================================================
+
+/// xHCI driver for the driver host
+pub struct XhciDriver {
+    device: Option<PciDevice>,
+    controller: Option<Xhci>,
+}
+
+impl XhciDriver {
+    fn matches(descriptor: &DeviceDescriptor) -> bool {
+        descriptor.protocol == bus_protocol::PCI
+            && descriptor.base_class == PCI_CLASS_SERIAL_BUS
+            && descriptor.sub_class == PCI_SUBCLASS_USB
+            && descriptor.prog_if == PCI_PROG_IF_XHCI
+    }
+
+    fn create() -> Box<dyn Driver> {
+        Box::new(XhciDriver {
+            device: None,
+            controller: None,
+        })
+    }
+}
+
+impl Driver for XhciDriver {
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()> {
+        let info = PciDeviceInfo {
+            vendor_id: descriptor.vendor_id,
+            device_id: descriptor.device_id,
+            base_class: descriptor.base_class,
+            sub_class: descriptor.sub_class,
+            prog_if: descriptor.prog_if,
+            ..Default::default()
+        };
+        let device = unsafe { PciDevice::from_handle(device, info) };
+        let mut controller = Xhci::new(&device)?;
+        controller.scan_ports();
+        self.device = Some(device);
+        self.controller = Some(controller);
+        Ok(())
+    }
+
+    fn unbind(&mut self) -> Result<()> {
+        if let Some(mut controller) = self.controller.take() {
+            controller.stop()?;
+        }
+        if let Some(device) = self.device.take() {
+            device.enable_bus_master(false)?;
+        }
+        Ok(())
+    }
+
+    /// Keyboards are instances from 1; the controller itself has no
+    /// class protocol
+    fn open(&mut self, instance: u32) -> Result<Connection> {
+        if instance == 0 {
+            return Err(Error::new(Status::NotSupported));
+        }
+        self.controller.as_mut().ok_or(Error::new(Status::BadState))?.open(instance)
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        match self.controller.as_mut() {
+            Some(controller) => controller.poll(),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Driver table entry
+pub const DRIVER: DriverEntry = DriverEntry {
+    name: "xhci",
+    matches: XhciDriver::matches,
+    create: XhciDriver::create,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_matches() {
+        let mut descriptor = DeviceDescriptor {
+            protocol: bus_protocol::PCI,
+            vendor_id: 0x1b36,
+            device_id: 0x000d,
+            base_class: 0x0C,
+            sub_class: 0x03,
+            prog_if: 0x30,
+            revision_id: 1,
+        };
+        assert!(XhciDriver::matches(&descriptor));
+        // EHCI
+        descriptor.prog_if = 0x20;
+        assert!(!XhciDriver::matches(&descriptor));
+    }
+
+    #[test]
+    fn test_packet_sizes_and_intervals() {
+        assert_eq!(default_max_packet0(speed::LOW), 8);
+        assert_eq!(default_max_packet0(speed::HIGH), 64);
+        assert_eq!(max_packet0(speed::FULL, 64), 64);
+        assert_eq!(max_packet0(speed::SUPER, 9), 512);
+
+        // 10 ms is 80 microframes, rounded down to 64
+        assert_eq!(interrupt_interval(speed::FULL, 10), 6);
+        assert_eq!(interrupt_interval(speed::LOW, 1), 3);
+        assert_eq!(interrupt_interval(speed::FULL, 255), 10);
+        assert_eq!(interrupt_interval(speed::HIGH, 4), 3);
+        assert_eq!(interrupt_interval(speed::SUPER, 0), 0);
+    }
+
+    #[test]
+    fn test_input_context() {
+        let mut memory = vec![Trb::default(); 4];
+        let ring = unsafe { Ring::from_raw(memory.as_mut_ptr(), 0x1_2345_6000, 4) };
+        let mut buf = vec![0xFFu8; 33 * 32];
+        let mut input = InputContext::new(&mut buf, 32);
+        input.add(0b11);
+        input.slot(speed::HIGH, 2, 1);
+        input.endpoint(1, ep_type::CONTROL, 64, 0, &ring);
+
+        let dword = |entry: usize, n: usize| {
+            let offset = entry * 32 + n * 4;
+            u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+        };
+        assert_eq!(dword(0, 0), 0);
+        assert_eq!(dword(0, 1), 0b11);
+        assert_eq!(dword(1, 0), 1 << 27 | 3 << 20);
+        assert_eq!(dword(1, 1), 2 << 16);
+        assert_eq!(dword(2, 1), 64 << 16 | 4 << 3 | 3 << 1);
+        assert_eq!(dword(2, 2), 0x2345_6001);
+        assert_eq!(dword(2, 3), 1);
+        assert_eq!(dword(2, 4), 64 << 16 | 8);
+        assert_eq!(dword(3, 0), 0);
+    }
+}
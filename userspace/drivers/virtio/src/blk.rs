@@ -0,0 +1,326 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtio Block Driver
+//!
+//! Each request is a three-part descriptor chain: a header naming the
+//! operation and sector, the data buffer, and a status byte written by
+//! the device. Requests go through one bounce buffer and are issued one
+//! at a time; the block server above batches clients' FIFO requests.
+//!
+//! The driver wraps the device in a `BlockStack`, so GPT partitions are
+//! published as instances 1 and up next to the whole disk.
+
+use alloc::boxed::Box;
+
+use libddk::block::check_range;
+use libddk::dma::PAGE_SIZE;
+use libddk::protocol::bus_protocol;
+use libddk::*;
+use libsys::{Error, Handle, Result, Status};
+
+use crate::pci::{device_type_from_pci, PciTransport, VIRTIO_PCI_VENDOR};
+use crate::queue::{Segment, Virtqueue, MAX_QUEUE_SIZE};
+use crate::transport::{self, device_type, Transport};
+
+/// Feature bits
+pub mod blk_features {
+    /// Device is read-only
+    pub const RO: u64 = 1 << 5;
+
+    /// `blk_size` holds the logical block size
+    pub const BLK_SIZE: u64 = 1 << 6;
+
+    /// Device supports cache flush
+    pub const FLUSH: u64 = 1 << 9;
+}
+
+/// Configuration space offsets
+mod config {
+    /// Capacity in 512-byte sectors (u64)
+    pub const CAPACITY: usize = 0;
+
+    /// Logical block size (u32)
+    pub const BLK_SIZE: usize = 20;
+}
+
+/// Request types
+mod req_type {
+    pub const IN: u32 = 0;
+    pub const OUT: u32 = 1;
+    pub const FLUSH: u32 = 4;
+}
+
+/// Request status values
+mod req_status {
+    pub const OK: u8 = 0;
+    pub const IOERR: u8 = 1;
+    pub const UNSUPP: u8 = 2;
+}
+
+/// Sector size used by the virtio-blk protocol
+pub const SECTOR_SIZE: u64 = 512;
+
+/// Largest transfer per request
+pub const MAX_TRANSFER: usize = 64 * 1024;
+
+/// Size of the I/O VMO offered to clients
+const IO_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Bounce buffer layout: header and status in the first page, data after
+const HEADER_OFFSET: usize = 0;
+const STATUS_OFFSET: usize = 16;
+const DATA_OFFSET: usize = PAGE_SIZE;
+
+/// Encode a request header
+fn request_header(kind: u32, sector: u64) -> [u8; 16] {
+    let mut header = [0u8; 16];
+    header[0..4].copy_from_slice(&kind.to_le_bytes());
+    header[8..16].copy_from_slice(&sector.to_le_bytes());
+    header
+}
+
+/// Map a request status byte to a result
+fn status_result(status: u8) -> Result<()> {
+    match status {
+        req_status::OK => Ok(()),
+        req_status::UNSUPP => Err(Error::new(Status::NotSupported)),
+        req_status::IOERR => Err(Error::new(Status::IoError)),
+        _ => Err(Error::new(Status::Internal)),
+    }
+}
+
+/// A virtio block device
+pub struct VirtioBlk {
+    transport: Box<dyn Transport>,
+    queue: Virtqueue,
+    buffer: DmaBuffer,
+    info: BlockInfo,
+    features: u64,
+    _bti: Bti,
+}
+
+impl VirtioBlk {
+    /// Initialize the device behind `transport`
+    pub fn new(mut transport: Box<dyn Transport>) -> Result<Self> {
+        if transport.device_type() != device_type::BLOCK {
+            return Err(Error::new(Status::WrongType));
+        }
+        let features = transport::negotiate(
+            &mut *transport,
+            blk_features::RO | blk_features::BLK_SIZE | blk_features::FLUSH,
+        )?;
+
+        let bti = Bti::create(0)?;
+        let queue = transport::setup_queue(&mut *transport, &bti, 0, MAX_QUEUE_SIZE)?;
+        let buffer = DmaBuffer::create(&bti, DATA_OFFSET + MAX_TRANSFER)?;
+
+        let block_size = if features & blk_features::BLK_SIZE != 0 {
+            transport.config_read32(config::BLK_SIZE)
+        } else {
+            SECTOR_SIZE as u32
+        };
+        if block_size < SECTOR_SIZE as u32 || !block_size.is_power_of_two() || block_size as usize > MAX_TRANSFER {
+            transport.set_status(transport::status::FAILED);
+            return Err(Error::new(Status::NotSupported));
+        }
+        let capacity = transport.config_read64(config::CAPACITY);
+        let info = BlockInfo {
+            block_size,
+            max_transfer: MAX_TRANSFER as u32,
+            block_count: capacity * SECTOR_SIZE / block_size as u64,
+        };
+
+        transport::driver_ok(&mut *transport);
+        Ok(Self {
+            transport,
+            queue,
+            buffer,
+            info,
+            features,
+            _bti: bti,
+        })
+    }
+
+    /// Whether the device rejects writes
+    pub fn read_only(&self) -> bool {
+        self.features & blk_features::RO != 0
+    }
+
+    /// Reset the device, stopping all queue processing
+    pub fn reset(&mut self) {
+        self.transport.set_status(0);
+    }
+
+    /// Issue one request with `len` bytes of data in the bounce buffer
+    fn submit(&mut self, kind: u32, sector: u64, len: usize) -> Result<()> {
+        let phys = self.buffer.phys();
+        {
+            let buf = self.buffer.as_mut_slice();
+            buf[HEADER_OFFSET..HEADER_OFFSET + 16].copy_from_slice(&request_header(kind, sector));
+            buf[STATUS_OFFSET] = 0xff;
+        }
+
+        let header = Segment {
+            addr: phys + HEADER_OFFSET as u64,
+            len: 16,
+            device_writes: false,
+        };
+        let data = Segment {
+            addr: phys + DATA_OFFSET as u64,
+            len: len as u32,
+            device_writes: kind == req_type::IN,
+        };
+        let status = Segment {
+            addr: phys + STATUS_OFFSET as u64,
+            len: 1,
+            device_writes: true,
+        };
+        let head = if len > 0 {
+            self.queue.add(&[header, data, status])?
+        } else {
+            self.queue.add(&[header, status])?
+        };
+        self.transport.notify(self.queue.index());
+
+        loop {
+            transport::service_interrupt(&mut *self.transport);
+            match self.queue.pop_used() {
+                Some((done, _)) if done == head => break,
+                // Only one request is outstanding at a time
+                Some(_) => return Err(Error::new(Status::Internal)),
+                None => core::hint::spin_loop(),
+            }
+        }
+
+        status_result(self.buffer.as_slice()[STATUS_OFFSET])
+    }
+
+    fn sectors_per_block(&self) -> u64 {
+        self.info.block_size as u64 / SECTOR_SIZE
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn info(&self) -> BlockInfo {
+        self.info
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let mut sector = lba * self.sectors_per_block();
+        for chunk in buf.chunks_mut(MAX_TRANSFER) {
+            self.submit(req_type::IN, sector, chunk.len())?;
+            chunk.copy_from_slice(&self.buffer.as_slice()[DATA_OFFSET..DATA_OFFSET + chunk.len()]);
+            sector += chunk.len() as u64 / SECTOR_SIZE;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        if self.read_only() {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        check_range(&self.info, lba, buf.len())?;
+        let mut sector = lba * self.sectors_per_block();
+        for chunk in buf.chunks(MAX_TRANSFER) {
+            self.buffer.as_mut_slice()[DATA_OFFSET..DATA_OFFSET + chunk.len()].copy_from_slice(chunk);
+            self.submit(req_type::OUT, sector, chunk.len())?;
+            sector += chunk.len() as u64 / SECTOR_SIZE;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.features & blk_features::FLUSH == 0 {
+            // No volatile write cache to flush
+            return Ok(());
+        }
+        self.submit(req_type::FLUSH, 0, 0)
+    }
+}
+
+/// ============================================================================
+/// Driver
+/// ============================================================================
+
+/// virtio-blk driver for the driver host
+pub struct VirtioBlkDriver {
+    stack: Option<BlockStack<VirtioBlk>>,
+}
+
+impl VirtioBlkDriver {
+    fn matches(descriptor: &DeviceDescriptor) -> bool {
+        descriptor.protocol == bus_protocol::PCI
+            && descriptor.vendor_id == VIRTIO_PCI_VENDOR
+            && device_type_from_pci(descriptor.device_id) == Some(device_type::BLOCK)
+    }
+
+    fn create() -> Box<dyn Driver> {
+        Box::new(VirtioBlkDriver { stack: None })
+    }
+}
+
+impl Driver for VirtioBlkDriver {
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()> {
+        let info = PciDeviceInfo {
+            vendor_id: descriptor.vendor_id,
+            device_id: descriptor.device_id,
+            ..Default::default()
+        };
+        let device = unsafe { PciDevice::from_handle(device, info) };
+        let transport = PciTransport::new(device)?;
+        self.stack = Some(BlockStack::new(VirtioBlk::new(Box::new(transport))?));
+        Ok(())
+    }
+
+    fn unbind(&mut self) -> Result<()> {
+        if let Some(stack) = self.stack.take() {
+            stack.into_inner().reset();
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, instance: u32) -> Result<Connection> {
+        let stack = self.stack.as_mut().ok_or(Error::new(Status::BadState))?;
+        stack.open(instance, IO_BUFFER_SIZE)
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        match self.stack.as_mut() {
+            Some(stack) => stack.poll(),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Driver table entry
+pub const DRIVER: DriverEntry = DriverEntry {
+    name: "virtio-blk",
+    matches: VirtioBlkDriver::matches,
+    create: VirtioBlkDriver::create,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_header() {
+        let header = request_header(req_type::OUT, 0x1122_3344_5566);
+        assert_eq!(&header[0..4], &[1, 0, 0, 0]);
+        assert_eq!(&header[4..8], &[0; 4]);
+        assert_eq!(u64::from_le_bytes(header[8..16].try_into().unwrap()), 0x1122_3344_5566);
+    }
+
+    #[test]
+    fn test_status_result() {
+        assert!(status_result(req_status::OK).is_ok());
+        assert_eq!(status_result(req_status::IOERR).unwrap_err().status(), Status::IoError);
+        assert_eq!(status_result(req_status::UNSUPP).unwrap_err().status(), Status::NotSupported);
+        assert!(status_result(0xff).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtio Drivers
+//!
+//! Virtio 1.0 core and device drivers for QEMU bring-up:
+//! - PCI (modern, capability based) and MMIO (version 2) transports
+//! - Split virtqueues with descriptor chain management
+//! - virtio-blk, exposed through the DDK block class
+//! - virtio-net, exposed through the DDK ethernet class
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::DriverEntry;
+//!
+//! static DRIVERS: [DriverEntry; 2] = [virtio::blk::DRIVER, virtio::net::DRIVER];
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod blk;
+pub mod mmio;
+pub mod net;
+pub mod pci;
+pub mod queue;
+pub mod transport;
+
+// Re-export commonly used types
+pub use blk::VirtioBlk;
+pub use mmio::MmioTransport;
+pub use net::VirtioNet;
+pub use pci::PciTransport;
+pub use queue::{Segment, Virtqueue};
+pub use transport::Transport;
@@ -0,0 +1,168 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtio MMIO Transport
+//!
+//! Version 2 (non-legacy) virtio-mmio register layout, as found on the
+//! QEMU `virt` machines. Devices are discovered from the device tree, so
+//! the caller supplies the register window and interrupt.
+
+use libddk::{Interrupt, MmioBuffer};
+use libsys::{Error, Handle, Result, Status};
+
+use crate::queue::Virtqueue;
+use crate::transport::Transport;
+
+/// "virt" in little endian
+const MAGIC_VALUE: u32 = 0x7472_6976;
+
+/// Size of a virtio-mmio register window
+pub const MMIO_WINDOW_SIZE: usize = 0x200;
+
+/// Register offsets
+mod reg {
+    pub const MAGIC: usize = 0x000;
+    pub const VERSION: usize = 0x004;
+    pub const DEVICE_ID: usize = 0x008;
+    pub const DEVICE_FEATURES: usize = 0x010;
+    pub const DEVICE_FEATURES_SEL: usize = 0x014;
+    pub const DRIVER_FEATURES: usize = 0x020;
+    pub const DRIVER_FEATURES_SEL: usize = 0x024;
+    pub const QUEUE_SEL: usize = 0x030;
+    pub const QUEUE_NUM_MAX: usize = 0x034;
+    pub const QUEUE_NUM: usize = 0x038;
+    pub const QUEUE_READY: usize = 0x044;
+    pub const QUEUE_NOTIFY: usize = 0x050;
+    pub const INTERRUPT_STATUS: usize = 0x060;
+    pub const INTERRUPT_ACK: usize = 0x064;
+    pub const STATUS: usize = 0x070;
+    pub const QUEUE_DESC_LOW: usize = 0x080;
+    pub const QUEUE_DESC_HIGH: usize = 0x084;
+    pub const QUEUE_DRIVER_LOW: usize = 0x090;
+    pub const QUEUE_DRIVER_HIGH: usize = 0x094;
+    pub const QUEUE_DEVICE_LOW: usize = 0x0a0;
+    pub const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+    pub const CONFIG: usize = 0x100;
+}
+
+/// Virtio device behind an MMIO window
+pub struct MmioTransport {
+    regs: MmioBuffer,
+    irq: Interrupt,
+    device_type: u32,
+}
+
+impl MmioTransport {
+    /// Wrap a mapped register window
+    ///
+    /// Fails with `Status::NotFound` for an empty slot (device ID 0) and
+    /// `Status::NotSupported` for legacy (version 1) devices.
+    pub fn new(regs: MmioBuffer, irq: Interrupt) -> Result<Self> {
+        if regs.size() < MMIO_WINDOW_SIZE || regs.read32(reg::MAGIC) != MAGIC_VALUE {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        if regs.read32(reg::VERSION) != 2 {
+            return Err(Error::new(Status::NotSupported));
+        }
+        let device_type = regs.read32(reg::DEVICE_ID);
+        if device_type == 0 {
+            return Err(Error::new(Status::NotFound));
+        }
+        Ok(Self {
+            regs,
+            irq,
+            device_type,
+        })
+    }
+
+    /// Map the window at `paddr` and bind interrupt `vector`
+    pub fn probe(resource: &Handle, paddr: u64, vector: u32) -> Result<Self> {
+        let regs = MmioBuffer::create_physical(resource, paddr, MMIO_WINDOW_SIZE)?;
+        let irq = Interrupt::create(resource, vector)?;
+        Self::new(regs, irq)
+    }
+
+    fn write_addr(&self, low: usize, high: usize, addr: u64) {
+        self.regs.write32(low, addr as u32);
+        self.regs.write32(high, (addr >> 32) as u32);
+    }
+}
+
+impl Transport for MmioTransport {
+    fn device_type(&self) -> u32 {
+        self.device_type
+    }
+
+    fn device_features(&mut self) -> u64 {
+        self.regs.write32(reg::DEVICE_FEATURES_SEL, 0);
+        let low = self.regs.read32(reg::DEVICE_FEATURES);
+        self.regs.write32(reg::DEVICE_FEATURES_SEL, 1);
+        let high = self.regs.read32(reg::DEVICE_FEATURES);
+        low as u64 | (high as u64) << 32
+    }
+
+    fn set_driver_features(&mut self, features: u64) {
+        self.regs.write32(reg::DRIVER_FEATURES_SEL, 0);
+        self.regs.write32(reg::DRIVER_FEATURES, features as u32);
+        self.regs.write32(reg::DRIVER_FEATURES_SEL, 1);
+        self.regs.write32(reg::DRIVER_FEATURES, (features >> 32) as u32);
+    }
+
+    fn status(&self) -> u8 {
+        self.regs.read32(reg::STATUS) as u8
+    }
+
+    fn set_status(&mut self, status: u8) {
+        self.regs.write32(reg::STATUS, status as u32);
+    }
+
+    fn max_queue_size(&mut self, index: u16) -> u16 {
+        self.regs.write32(reg::QUEUE_SEL, index as u32);
+        if self.regs.read32(reg::QUEUE_READY) != 0 {
+            // Already in use
+            return 0;
+        }
+        self.regs.read32(reg::QUEUE_NUM_MAX).min(u16::MAX as u32) as u16
+    }
+
+    fn enable_queue(&mut self, queue: &Virtqueue) -> Result<()> {
+        self.regs.write32(reg::QUEUE_SEL, queue.index() as u32);
+        self.regs.write32(reg::QUEUE_NUM, queue.size() as u32);
+        self.write_addr(reg::QUEUE_DESC_LOW, reg::QUEUE_DESC_HIGH, queue.desc_addr());
+        self.write_addr(reg::QUEUE_DRIVER_LOW, reg::QUEUE_DRIVER_HIGH, queue.avail_addr());
+        self.write_addr(reg::QUEUE_DEVICE_LOW, reg::QUEUE_DEVICE_HIGH, queue.used_addr());
+        self.regs.write32(reg::QUEUE_READY, 1);
+        Ok(())
+    }
+
+    fn notify(&mut self, index: u16) {
+        self.regs.write32(reg::QUEUE_NOTIFY, index as u32);
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        let status = self.regs.read32(reg::INTERRUPT_STATUS);
+        if status != 0 {
+            self.regs.write32(reg::INTERRUPT_ACK, status);
+        }
+        status != 0
+    }
+
+    fn interrupt(&self) -> &Interrupt {
+        &self.irq
+    }
+
+    fn config_read8(&self, offset: usize) -> u8 {
+        self.regs.read8(reg::CONFIG + offset)
+    }
+
+    fn config_read16(&self, offset: usize) -> u16 {
+        self.regs.read16(reg::CONFIG + offset)
+    }
+
+    fn config_read32(&self, offset: usize) -> u32 {
+        self.regs.read32(reg::CONFIG + offset)
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtio Network Driver
+//!
+//! Queue 0 receives and queue 1 transmits. Each queue owns a DMA buffer
+//! split into fixed-size slots; every slot holds a `virtio_net_hdr`
+//! followed by one frame and travels as a single descriptor. Receive
+//! slots are all posted at start-up and reposted as frames are consumed.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use libddk::protocol::bus_protocol;
+use libddk::*;
+use libsys::{Error, Handle, Result, Status};
+
+use crate::pci::{device_type_from_pci, PciTransport, VIRTIO_PCI_VENDOR};
+use crate::queue::{Segment, Virtqueue};
+use crate::transport::{self, device_type, Transport};
+
+/// Feature bits
+pub mod net_features {
+    /// `mtu` is valid
+    pub const MTU: u64 = 1 << 3;
+
+    /// `mac` is valid
+    pub const MAC: u64 = 1 << 5;
+
+    /// `status` is valid
+    pub const STATUS: u64 = 1 << 16;
+}
+
+/// Configuration space offsets
+mod config {
+    pub const MAC: usize = 0;
+    pub const STATUS: usize = 6;
+    pub const MTU: usize = 10;
+}
+
+/// Link is up (`status` field)
+const NET_S_LINK_UP: u16 = 1;
+
+/// Size of `virtio_net_hdr` with VERSION_1
+pub const NET_HEADER_SIZE: usize = 12;
+
+/// Size of each receive and transmit slot
+pub const BUFFER_SIZE: usize = 2048;
+
+/// Queue indices
+const RX_QUEUE: u16 = 0;
+const TX_QUEUE: u16 = 1;
+
+/// Slots per queue
+const QUEUE_SIZE: u16 = 128;
+
+/// MTU assumed when the device does not report one
+const DEFAULT_MTU: u16 = 1500;
+
+/// Locally administered MAC used when the device does not report one
+const DEFAULT_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+/// Size of the I/O VMO offered to clients
+const IO_BUFFER_SIZE: usize = 256 * BUFFER_SIZE;
+
+/// Frame bytes of receive slot `slot` after the device wrote `used` bytes
+fn rx_frame_range(slot: usize, used: usize) -> Option<Range<usize>> {
+    if used <= NET_HEADER_SIZE || used > BUFFER_SIZE {
+        return None;
+    }
+    let base = slot * BUFFER_SIZE;
+    Some(base + NET_HEADER_SIZE..base + used)
+}
+
+/// A virtio network device
+pub struct VirtioNet {
+    transport: Box<dyn Transport>,
+    features: u64,
+    info: EthernetInfo,
+
+    rx_queue: Virtqueue,
+    rx_buffer: DmaBuffer,
+
+    /// Receive slot for each descriptor head
+    rx_slots: Vec<u16>,
+
+    tx_queue: Virtqueue,
+    tx_buffer: DmaBuffer,
+
+    /// Transmit slot for each descriptor head
+    tx_slots: Vec<u16>,
+
+    /// Transmit slots not owned by the device
+    tx_free: Vec<u16>,
+
+    _bti: Bti,
+}
+
+impl VirtioNet {
+    /// Initialize the device behind `transport`
+    pub fn new(mut transport: Box<dyn Transport>) -> Result<Self> {
+        if transport.device_type() != device_type::NET {
+            return Err(Error::new(Status::WrongType));
+        }
+        let features = transport::negotiate(
+            &mut *transport,
+            net_features::MAC | net_features::STATUS | net_features::MTU,
+        )?;
+
+        let bti = Bti::create(0)?;
+        let rx_queue = transport::setup_queue(&mut *transport, &bti, RX_QUEUE, QUEUE_SIZE)?;
+        let tx_queue = transport::setup_queue(&mut *transport, &bti, TX_QUEUE, QUEUE_SIZE)?;
+        let rx_buffer = DmaBuffer::create(&bti, rx_queue.size() as usize * BUFFER_SIZE)?;
+        let tx_buffer = DmaBuffer::create(&bti, tx_queue.size() as usize * BUFFER_SIZE)?;
+
+        let mut mac = DEFAULT_MAC;
+        if features & net_features::MAC != 0 {
+            for (i, byte) in mac.iter_mut().enumerate() {
+                *byte = transport.config_read8(config::MAC + i);
+            }
+        }
+        let mtu = if features & net_features::MTU != 0 {
+            transport.config_read16(config::MTU)
+        } else {
+            DEFAULT_MTU
+        };
+        // Frames must fit a slot
+        let mtu = mtu.min((BUFFER_SIZE - NET_HEADER_SIZE - 14) as u16);
+
+        let mut net = Self {
+            transport,
+            features,
+            info: EthernetInfo { mac, mtu },
+            rx_slots: alloc::vec![0; rx_queue.size() as usize],
+            rx_queue,
+            rx_buffer,
+            tx_slots: alloc::vec![0; tx_queue.size() as usize],
+            tx_free: (0..tx_queue.size()).rev().collect(),
+            tx_queue,
+            tx_buffer,
+            _bti: bti,
+        };
+
+        transport::driver_ok(&mut *net.transport);
+        for slot in 0..net.rx_queue.size() {
+            net.post_rx(slot)?;
+        }
+        net.transport.notify(RX_QUEUE);
+        Ok(net)
+    }
+
+    /// Whether the link is up
+    ///
+    /// Devices without link status reporting are always up.
+    pub fn link_up(&self) -> bool {
+        if self.features & net_features::STATUS == 0 {
+            return true;
+        }
+        self.transport.config_read16(config::STATUS) & NET_S_LINK_UP != 0
+    }
+
+    /// Reset the device, stopping all queue processing
+    pub fn reset(&mut self) {
+        self.transport.set_status(0);
+    }
+
+    /// Hand receive slot `slot` to the device
+    fn post_rx(&mut self, slot: u16) -> Result<()> {
+        let segment = Segment {
+            addr: self.rx_buffer.phys() + (slot as usize * BUFFER_SIZE) as u64,
+            len: BUFFER_SIZE as u32,
+            device_writes: true,
+        };
+        let head = self.rx_queue.add(&[segment])?;
+        self.rx_slots[head as usize] = slot;
+        Ok(())
+    }
+
+    /// Return completed transmit slots to the free list
+    fn reclaim_tx(&mut self) {
+        while let Some((head, _)) = self.tx_queue.pop_used() {
+            self.tx_free.push(self.tx_slots[head as usize]);
+        }
+    }
+}
+
+impl EthernetDevice for VirtioNet {
+    fn info(&self) -> EthernetInfo {
+        self.info
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<()> {
+        if frame.is_empty() || frame.len() > BUFFER_SIZE - NET_HEADER_SIZE {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        transport::service_interrupt(&mut *self.transport);
+        self.reclaim_tx();
+        let slot = self.tx_free.pop().ok_or(Error::new(Status::Busy))?;
+
+        let base = slot as usize * BUFFER_SIZE;
+        let buf = &mut self.tx_buffer.as_mut_slice()[base..base + NET_HEADER_SIZE + frame.len()];
+        buf[..NET_HEADER_SIZE].fill(0);
+        buf[NET_HEADER_SIZE..].copy_from_slice(frame);
+
+        let segment = Segment {
+            addr: self.tx_buffer.phys() + base as u64,
+            len: (NET_HEADER_SIZE + frame.len()) as u32,
+            device_writes: false,
+        };
+        let head = match self.tx_queue.add(&[segment]) {
+            Ok(head) => head,
+            Err(err) => {
+                self.tx_free.push(slot);
+                return Err(err);
+            }
+        };
+        self.tx_slots[head as usize] = slot;
+        self.transport.notify(TX_QUEUE);
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+        transport::service_interrupt(&mut *self.transport);
+        loop {
+            let (head, used) = match self.rx_queue.pop_used() {
+                Some(done) => done,
+                None => return Ok(0),
+            };
+            let slot = self.rx_slots[head as usize];
+
+            let len = match rx_frame_range(slot as usize, used as usize) {
+                Some(range) if range.len() <= buf.len() => {
+                    buf[..range.len()].copy_from_slice(&self.rx_buffer.as_slice()[range.clone()]);
+                    range.len()
+                }
+                // Runt or oversized frame; drop it
+                _ => 0,
+            };
+
+            self.post_rx(slot)?;
+            self.transport.notify(RX_QUEUE);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+/// ============================================================================
+/// Driver
+/// ============================================================================
+
+/// virtio-net driver for the driver host
+pub struct VirtioNetDriver {
+    device: Option<VirtioNet>,
+    server: Option<EthernetServer>,
+}
+
+impl VirtioNetDriver {
+    fn matches(descriptor: &DeviceDescriptor) -> bool {
+        descriptor.protocol == bus_protocol::PCI
+            && descriptor.vendor_id == VIRTIO_PCI_VENDOR
+            && device_type_from_pci(descriptor.device_id) == Some(device_type::NET)
+    }
+
+    fn create() -> Box<dyn Driver> {
+        Box::new(VirtioNetDriver {
+            device: None,
+            server: None,
+        })
+    }
+}
+
+impl Driver for VirtioNetDriver {
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()> {
+        let info = PciDeviceInfo {
+            vendor_id: descriptor.vendor_id,
+            device_id: descriptor.device_id,
+            ..Default::default()
+        };
+        let device = unsafe { PciDevice::from_handle(device, info) };
+        let transport = PciTransport::new(device)?;
+        self.device = Some(VirtioNet::new(Box::new(transport))?);
+        Ok(())
+    }
+
+    fn unbind(&mut self) -> Result<()> {
+        self.server = None;
+        if let Some(mut device) = self.device.take() {
+            device.reset();
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, instance: u32) -> Result<Connection> {
+        if instance != 0 {
+            return Err(Error::new(Status::NotFound));
+        }
+        let device = self.device.as_ref().ok_or(Error::new(Status::BadState))?;
+        // One client at a time; a new open replaces the previous one
+        let (server, connection) = EthernetServer::create(&device.info(), IO_BUFFER_SIZE)?;
+        self.server = Some(server);
+        Ok(connection)
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        match (self.device.as_mut(), self.server.as_mut()) {
+            (Some(device), Some(server)) => server.poll(device),
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Driver table entry
+pub const DRIVER: DriverEntry = DriverEntry {
+    name: "virtio-net",
+    matches: VirtioNetDriver::matches,
+    create: VirtioNetDriver::create,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rx_frame_range() {
+        assert_eq!(rx_frame_range(0, 60 + NET_HEADER_SIZE), Some(NET_HEADER_SIZE..60 + NET_HEADER_SIZE));
+        assert_eq!(rx_frame_range(2, BUFFER_SIZE), Some(2 * BUFFER_SIZE + NET_HEADER_SIZE..3 * BUFFER_SIZE));
+        assert_eq!(rx_frame_range(0, NET_HEADER_SIZE), None);
+        assert_eq!(rx_frame_range(0, BUFFER_SIZE + 1), None);
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtio PCI Transport
+//!
+//! Modern virtio PCI devices describe their register blocks with
+//! vendor-specific capabilities, each naming a BAR and an offset:
+//! common configuration, notification, ISR status and device
+//! configuration. Transitional devices expose the same capabilities next
+//! to the legacy I/O BAR, which this transport ignores.
+
+use alloc::vec::Vec;
+
+use libddk::pci::irq_mode;
+use libddk::{Interrupt, MmioBuffer, PciDevice};
+use libsys::{Error, Result, Status};
+
+use crate::queue::Virtqueue;
+use crate::transport::{device_type, Transport};
+
+/// PCI vendor ID for virtio devices
+pub const VIRTIO_PCI_VENDOR: u16 = 0x1af4;
+
+/// Vendor-specific capability ID
+const PCI_CAP_ID_VENDOR: u8 = 0x09;
+
+/// Capability types
+const CAP_COMMON_CFG: u8 = 1;
+const CAP_NOTIFY_CFG: u8 = 2;
+const CAP_ISR_CFG: u8 = 3;
+const CAP_DEVICE_CFG: u8 = 4;
+
+/// Common configuration registers
+mod common {
+    pub const DEVICE_FEATURE_SELECT: usize = 0x00;
+    pub const DEVICE_FEATURE: usize = 0x04;
+    pub const DRIVER_FEATURE_SELECT: usize = 0x08;
+    pub const DRIVER_FEATURE: usize = 0x0c;
+    pub const MSIX_CONFIG: usize = 0x10;
+    pub const NUM_QUEUES: usize = 0x12;
+    pub const DEVICE_STATUS: usize = 0x14;
+    pub const QUEUE_SELECT: usize = 0x16;
+    pub const QUEUE_SIZE: usize = 0x18;
+    pub const QUEUE_MSIX_VECTOR: usize = 0x1a;
+    pub const QUEUE_ENABLE: usize = 0x1c;
+    pub const QUEUE_NOTIFY_OFF: usize = 0x1e;
+    pub const QUEUE_DESC: usize = 0x20;
+    pub const QUEUE_DRIVER: usize = 0x28;
+    pub const QUEUE_DEVICE: usize = 0x30;
+}
+
+/// MSI-X vector value meaning "no vector"
+const VIRTIO_MSI_NO_VECTOR: u16 = 0xffff;
+
+/// Map a PCI device ID to a virtio device type
+///
+/// Modern devices use 0x1040 + type; transitional devices use the legacy
+/// 0x1000-0x103f range.
+pub fn device_type_from_pci(device_id: u16) -> Option<u32> {
+    match device_id {
+        0x1040..=0x107f => Some((device_id - 0x1040) as u32),
+        0x1000 => Some(device_type::NET),
+        0x1001 => Some(device_type::BLOCK),
+        0x1002 => Some(device_type::BALLOON),
+        0x1003 => Some(device_type::CONSOLE),
+        0x1004 => Some(device_type::SCSI),
+        0x1005 => Some(device_type::ENTROPY),
+        0x1009 => Some(device_type::P9),
+        _ => None,
+    }
+}
+
+/// Register block location: BAR index and byte offset
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    bar: u8,
+    offset: usize,
+}
+
+/// Virtio device on PCI
+pub struct PciTransport {
+    device: PciDevice,
+    device_type: u32,
+
+    /// Mapped BARs, indexed by BAR number
+    bars: [Option<MmioBuffer>; 6],
+
+    common: Region,
+    notify: Region,
+    notify_multiplier: u32,
+    isr: Region,
+    device_cfg: Option<Region>,
+
+    /// Per-queue notification offsets, filled by `enable_queue`
+    notify_offsets: Vec<u16>,
+
+    irq: Interrupt,
+    msix: bool,
+}
+
+impl PciTransport {
+    /// Probe the virtio capabilities of `device` and map its registers
+    pub fn new(device: PciDevice) -> Result<Self> {
+        let info = *device.info();
+        if info.vendor_id != VIRTIO_PCI_VENDOR {
+            return Err(Error::new(Status::NotSupported));
+        }
+        let device_type = device_type_from_pci(info.device_id).ok_or(Error::new(Status::NotSupported))?;
+
+        let mut common = None;
+        let mut notify = None;
+        let mut notify_multiplier = 0;
+        let mut isr = None;
+        let mut device_cfg = None;
+
+        // Status register bit 4: capability list present
+        if device.config_read16(0x06)? & 0x10 == 0 {
+            return Err(Error::new(Status::NotSupported));
+        }
+        let mut ptr = device.config_read8(0x34)? & !0x3;
+        let mut guard = 0;
+        while ptr != 0 && guard < 48 {
+            let cap = ptr as u16;
+            let id = device.config_read8(cap)?;
+            let next = device.config_read8(cap + 1)?;
+            if id == PCI_CAP_ID_VENDOR {
+                let cfg_type = device.config_read8(cap + 3)?;
+                let region = Region {
+                    bar: device.config_read8(cap + 4)?,
+                    offset: device.config_read32(cap + 8)? as usize,
+                };
+                match cfg_type {
+                    CAP_COMMON_CFG if common.is_none() => common = Some(region),
+                    CAP_NOTIFY_CFG if notify.is_none() => {
+                        notify = Some(region);
+                        notify_multiplier = device.config_read32(cap + 16)?;
+                    }
+                    CAP_ISR_CFG if isr.is_none() => isr = Some(region),
+                    CAP_DEVICE_CFG if device_cfg.is_none() => device_cfg = Some(region),
+                    _ => {}
+                }
+            }
+            ptr = next & !0x3;
+            guard += 1;
+        }
+
+        let (common, notify, isr) = match (common, notify, isr) {
+            (Some(c), Some(n), Some(i)) => (c, n, i),
+            // No modern interface
+            _ => return Err(Error::new(Status::NotSupported)),
+        };
+
+        let mut bars: [Option<MmioBuffer>; 6] = Default::default();
+        for region in [Some(common), Some(notify), Some(isr), device_cfg].into_iter().flatten() {
+            let bar = region.bar as usize;
+            if bar >= bars.len() {
+                return Err(Error::new(Status::InvalidArgs));
+            }
+            if bars[bar].is_none() {
+                bars[bar] = Some(device.map_bar(bar as u32)?);
+            }
+        }
+
+        // Prefer one MSI-X vector for everything, else the legacy line
+        let msix = matches!(device.query_irq_mode(irq_mode::MSI_X), Ok(n) if n > 0);
+        if msix {
+            device.set_irq_mode(irq_mode::MSI_X, 1)?;
+        } else {
+            device.set_irq_mode(irq_mode::LEGACY, 1)?;
+        }
+        let irq = device.map_interrupt(0)?;
+        device.enable_bus_master(true)?;
+
+        let mut transport = Self {
+            device,
+            device_type,
+            bars,
+            common,
+            notify,
+            notify_multiplier,
+            isr,
+            device_cfg,
+            notify_offsets: Vec::new(),
+            irq,
+            msix,
+        };
+
+        let num_queues = transport.common_read16(common::NUM_QUEUES);
+        transport.notify_offsets.resize(num_queues as usize, 0);
+        if msix {
+            transport.common_write16(common::MSIX_CONFIG, 0);
+        }
+        Ok(transport)
+    }
+
+    /// Underlying PCI device
+    pub fn device(&self) -> &PciDevice {
+        &self.device
+    }
+
+    fn bar(&self, region: Region) -> &MmioBuffer {
+        // Every region's BAR was mapped in `new`
+        self.bars[region.bar as usize].as_ref().unwrap()
+    }
+
+    fn common_read8(&self, reg: usize) -> u8 {
+        self.bar(self.common).read8(self.common.offset + reg)
+    }
+
+    fn common_read16(&self, reg: usize) -> u16 {
+        self.bar(self.common).read16(self.common.offset + reg)
+    }
+
+    fn common_read32(&self, reg: usize) -> u32 {
+        self.bar(self.common).read32(self.common.offset + reg)
+    }
+
+    fn common_write8(&self, reg: usize, value: u8) {
+        self.bar(self.common).write8(self.common.offset + reg, value)
+    }
+
+    fn common_write16(&self, reg: usize, value: u16) {
+        self.bar(self.common).write16(self.common.offset + reg, value)
+    }
+
+    fn common_write32(&self, reg: usize, value: u32) {
+        self.bar(self.common).write32(self.common.offset + reg, value)
+    }
+
+    fn common_write64(&self, reg: usize, value: u64) {
+        self.common_write32(reg, value as u32);
+        self.common_write32(reg + 4, (value >> 32) as u32);
+    }
+
+    fn device_cfg(&self) -> Region {
+        // Only device types that define configuration fields read it
+        self.device_cfg.expect("virtio device has no configuration space")
+    }
+}
+
+impl Transport for PciTransport {
+    fn device_type(&self) -> u32 {
+        self.device_type
+    }
+
+    fn device_features(&mut self) -> u64 {
+        self.common_write32(common::DEVICE_FEATURE_SELECT, 0);
+        let low = self.common_read32(common::DEVICE_FEATURE);
+        self.common_write32(common::DEVICE_FEATURE_SELECT, 1);
+        let high = self.common_read32(common::DEVICE_FEATURE);
+        low as u64 | (high as u64) << 32
+    }
+
+    fn set_driver_features(&mut self, features: u64) {
+        self.common_write32(common::DRIVER_FEATURE_SELECT, 0);
+        self.common_write32(common::DRIVER_FEATURE, features as u32);
+        self.common_write32(common::DRIVER_FEATURE_SELECT, 1);
+        self.common_write32(common::DRIVER_FEATURE, (features >> 32) as u32);
+    }
+
+    fn status(&self) -> u8 {
+        self.common_read8(common::DEVICE_STATUS)
+    }
+
+    fn set_status(&mut self, status: u8) {
+        self.common_write8(common::DEVICE_STATUS, status);
+    }
+
+    fn max_queue_size(&mut self, index: u16) -> u16 {
+        if index as usize >= self.notify_offsets.len() {
+            return 0;
+        }
+        self.common_write16(common::QUEUE_SELECT, index);
+        self.common_read16(common::QUEUE_SIZE)
+    }
+
+    fn enable_queue(&mut self, queue: &Virtqueue) -> Result<()> {
+        let index = queue.index();
+        if index as usize >= self.notify_offsets.len() {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        self.common_write16(common::QUEUE_SELECT, index);
+        self.common_write16(common::QUEUE_SIZE, queue.size());
+        if self.msix {
+            self.common_write16(common::QUEUE_MSIX_VECTOR, 0);
+            if self.common_read16(common::QUEUE_MSIX_VECTOR) == VIRTIO_MSI_NO_VECTOR {
+                return Err(Error::new(Status::NoMemory));
+            }
+        }
+        self.common_write64(common::QUEUE_DESC, queue.desc_addr());
+        self.common_write64(common::QUEUE_DRIVER, queue.avail_addr());
+        self.common_write64(common::QUEUE_DEVICE, queue.used_addr());
+        self.notify_offsets[index as usize] = self.common_read16(common::QUEUE_NOTIFY_OFF);
+        self.common_write16(common::QUEUE_ENABLE, 1);
+        Ok(())
+    }
+
+    fn notify(&mut self, index: u16) {
+        let offset = self.notify.offset
+            + self.notify_offsets[index as usize] as usize * self.notify_multiplier as usize;
+        self.bar(self.notify).write16(offset, index);
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        // Reading the ISR clears it; MSI-X deliveries do not set it
+        let isr = self.bar(self.isr).read8(self.isr.offset);
+        self.msix || isr != 0
+    }
+
+    fn interrupt(&self) -> &Interrupt {
+        &self.irq
+    }
+
+    fn config_read8(&self, offset: usize) -> u8 {
+        let region = self.device_cfg();
+        self.bar(region).read8(region.offset + offset)
+    }
+
+    fn config_read16(&self, offset: usize) -> u16 {
+        let region = self.device_cfg();
+        self.bar(region).read16(region.offset + offset)
+    }
+
+    fn config_read32(&self, offset: usize) -> u32 {
+        let region = self.device_cfg();
+        self.bar(region).read32(region.offset + offset)
+    }
+}
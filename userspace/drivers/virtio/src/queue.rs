@@ -0,0 +1,350 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Split Virtqueues
+//!
+//! A split virtqueue lives in one physically contiguous buffer holding
+//! three areas:
+//!
+//! ```text
+//! +0            descriptor table   16 * size bytes
+//! avail_offset  available ring     flags, idx, ring[size], used_event
+//! used_offset   used ring          flags, idx, ring[size] {id, len}, avail_event
+//! ```
+//!
+//! Free descriptors are kept on a list threaded through their `next`
+//! fields. `add` takes a chain off the list and publishes its head in
+//! the available ring; `pop_used` returns completed chains to it.
+
+use core::ptr;
+use core::sync::atomic::{fence, Ordering};
+
+use libddk::{Bti, DmaBuffer};
+use libsys::{Error, Result, Status};
+
+/// Descriptor continues in `next`
+pub const VIRTQ_DESC_F_NEXT: u16 = 0x1;
+
+/// Buffer is written by the device
+pub const VIRTQ_DESC_F_WRITE: u16 = 0x2;
+
+/// Largest queue this driver sets up
+pub const MAX_QUEUE_SIZE: u16 = 256;
+
+/// Descriptor table entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Descriptor {
+    pub addr: u64,
+    pub len: u32,
+    pub flags: u16,
+    pub next: u16,
+}
+
+/// Used ring entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsedElem {
+    /// Head of the completed chain
+    pub id: u32,
+
+    /// Bytes written by the device
+    pub len: u32,
+}
+
+/// One buffer of a descriptor chain
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    /// Device-visible address
+    pub addr: u64,
+
+    /// Length in bytes
+    pub len: u32,
+
+    /// Whether the device writes (rather than reads) the buffer
+    pub device_writes: bool,
+}
+
+/// Offsets of the virtqueue areas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueLayout {
+    pub avail_offset: usize,
+    pub used_offset: usize,
+    pub size: usize,
+}
+
+impl QueueLayout {
+    /// Layout for a queue of `queue_size` entries
+    pub const fn new(queue_size: u16) -> Self {
+        let n = queue_size as usize;
+        let avail_offset = 16 * n;
+        let used_offset = (avail_offset + 6 + 2 * n + 3) & !3;
+        Self {
+            avail_offset,
+            used_offset,
+            size: used_offset + 6 + 8 * n,
+        }
+    }
+}
+
+/// Split virtqueue
+pub struct Virtqueue {
+    index: u16,
+    size: u16,
+    layout: QueueLayout,
+
+    /// CPU address of the queue memory
+    base: *mut u8,
+
+    /// Device address of the queue memory
+    phys: u64,
+
+    free_head: u16,
+    num_free: u16,
+
+    /// Next available ring slot (free-running)
+    avail_idx: u16,
+
+    /// Next used ring slot to consume (free-running)
+    last_used: u16,
+
+    /// Backing memory, when allocated by `create`
+    _mem: Option<DmaBuffer>,
+}
+
+impl Virtqueue {
+    /// Allocate queue `index` with `size` entries
+    ///
+    /// `size` must be a power of two no larger than `MAX_QUEUE_SIZE`.
+    pub fn create(bti: &Bti, index: u16, size: u16) -> Result<Self> {
+        let mem = DmaBuffer::create(bti, QueueLayout::new(size).size)?;
+        let mut queue = unsafe { Self::from_raw(index, size, mem.as_ptr(), mem.phys())? };
+        queue._mem = Some(mem);
+        Ok(queue)
+    }
+
+    /// Lay out a queue in caller-provided memory
+    ///
+    /// # Safety
+    ///
+    /// `base` must be valid, 16-byte aligned and at least
+    /// `QueueLayout::new(size).size` bytes long, and `phys` must be its
+    /// device address. The memory must outlive the queue.
+    pub unsafe fn from_raw(index: u16, size: u16, base: *mut u8, phys: u64) -> Result<Self> {
+        if size == 0 || !size.is_power_of_two() || size > MAX_QUEUE_SIZE {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let layout = QueueLayout::new(size);
+        ptr::write_bytes(base, 0, layout.size);
+
+        let mut queue = Self {
+            index,
+            size,
+            layout,
+            base,
+            phys,
+            free_head: 0,
+            num_free: size,
+            avail_idx: 0,
+            last_used: 0,
+            _mem: None,
+        };
+        for i in 0..size {
+            queue.desc(i).next = i.wrapping_add(1);
+        }
+        Ok(queue)
+    }
+
+    /// Queue index
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Number of entries
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Number of free descriptors
+    pub fn num_free(&self) -> u16 {
+        self.num_free
+    }
+
+    /// Device address of the descriptor table
+    pub fn desc_addr(&self) -> u64 {
+        self.phys
+    }
+
+    /// Device address of the available ring
+    pub fn avail_addr(&self) -> u64 {
+        self.phys + self.layout.avail_offset as u64
+    }
+
+    /// Device address of the used ring
+    pub fn used_addr(&self) -> u64 {
+        self.phys + self.layout.used_offset as u64
+    }
+
+    fn desc(&mut self, i: u16) -> &mut Descriptor {
+        unsafe { &mut *(self.base as *mut Descriptor).add(i as usize) }
+    }
+
+    fn avail_field(&self, i: usize) -> *mut u16 {
+        unsafe { (self.base.add(self.layout.avail_offset) as *mut u16).add(i) }
+    }
+
+    fn used_idx(&self) -> u16 {
+        unsafe { ptr::read_volatile((self.base.add(self.layout.used_offset) as *const u16).add(1)) }
+    }
+
+    fn used_elem(&self, slot: u16) -> UsedElem {
+        unsafe {
+            let ring = self.base.add(self.layout.used_offset + 4) as *const UsedElem;
+            ptr::read_volatile(ring.add(slot as usize))
+        }
+    }
+
+    /// Publish a descriptor chain to the device
+    ///
+    /// Returns the chain's head, which `pop_used` reports on completion.
+    /// The caller notifies the device afterwards.
+    pub fn add(&mut self, segments: &[Segment]) -> Result<u16> {
+        if segments.is_empty() {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        if segments.len() > self.num_free as usize {
+            return Err(Error::new(Status::NoMemory));
+        }
+
+        let head = self.free_head;
+        let mut idx = head;
+        for (i, seg) in segments.iter().enumerate() {
+            let last = i + 1 == segments.len();
+            let desc = self.desc(idx);
+            desc.addr = seg.addr;
+            desc.len = seg.len;
+            desc.flags = if seg.device_writes { VIRTQ_DESC_F_WRITE } else { 0 };
+            let next = desc.next;
+            if last {
+                self.free_head = next;
+            } else {
+                desc.flags |= VIRTQ_DESC_F_NEXT;
+                idx = next;
+            }
+        }
+        self.num_free -= segments.len() as u16;
+
+        let slot = self.avail_idx & (self.size - 1);
+        unsafe {
+            ptr::write_volatile(self.avail_field(2 + slot as usize), head);
+        }
+        // Descriptors and ring entry must be visible before the index
+        fence(Ordering::Release);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        unsafe {
+            ptr::write_volatile(self.avail_field(1), self.avail_idx);
+        }
+        fence(Ordering::SeqCst);
+
+        Ok(head)
+    }
+
+    /// Take the next completed chain
+    ///
+    /// Returns its head and the number of bytes the device wrote.
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        if self.last_used == self.used_idx() {
+            return None;
+        }
+        fence(Ordering::Acquire);
+
+        let elem = self.used_elem(self.last_used & (self.size - 1));
+        self.last_used = self.last_used.wrapping_add(1);
+
+        let head = elem.id as u16;
+        self.free_chain(head);
+        Some((head, elem.len))
+    }
+
+    fn free_chain(&mut self, head: u16) {
+        let mut idx = head;
+        let mut count = 1;
+        while self.desc(idx).flags & VIRTQ_DESC_F_NEXT != 0 {
+            idx = self.desc(idx).next;
+            count += 1;
+        }
+        let free_head = self.free_head;
+        self.desc(idx).next = free_head;
+        self.free_head = head;
+        self.num_free += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Act as the device: complete `head` with `len` bytes written
+    fn complete(queue: &Virtqueue, head: u16, len: u32) {
+        unsafe {
+            let used = queue.base.add(queue.layout.used_offset);
+            let idx = ptr::read((used as *const u16).add(1));
+            let ring = used.add(4) as *mut UsedElem;
+            ptr::write(ring.add((idx & (queue.size - 1)) as usize), UsedElem { id: head as u32, len });
+            ptr::write((used as *mut u16).add(1), idx.wrapping_add(1));
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = QueueLayout::new(8);
+        assert_eq!(layout.avail_offset, 128);
+        assert_eq!(layout.used_offset, 152);
+        assert_eq!(layout.size, 152 + 6 + 64);
+    }
+
+    #[test]
+    fn test_add_and_complete() {
+        let mut mem = alloc::vec![0u64; QueueLayout::new(4).size / 8 + 1];
+        let mut queue = unsafe { Virtqueue::from_raw(0, 4, mem.as_mut_ptr() as *mut u8, 0x1000).unwrap() };
+        assert_eq!(queue.avail_addr(), 0x1000 + 64);
+
+        let seg = |addr, device_writes| Segment { addr, len: 16, device_writes };
+        let a = queue.add(&[seg(0x10, false), seg(0x20, true)]).unwrap();
+        let b = queue.add(&[seg(0x30, false), seg(0x40, true)]).unwrap();
+        assert_eq!(queue.num_free(), 0);
+        assert!(queue.add(&[seg(0x50, false)]).is_err());
+
+        // The second descriptor of a chain is device-writable and last
+        let next = queue.desc(a).next;
+        let second = *queue.desc(next);
+        assert_eq!(second.addr, 0x20);
+        assert_eq!(second.flags, VIRTQ_DESC_F_WRITE);
+        assert_eq!(unsafe { ptr::read(queue.avail_field(1)) }, 2);
+
+        assert_eq!(queue.pop_used(), None);
+        complete(&queue, b, 7);
+        assert_eq!(queue.pop_used(), Some((b, 7)));
+        assert_eq!(queue.num_free(), 2);
+
+        // Freed descriptors are reused
+        let c = queue.add(&[seg(0x60, false), seg(0x70, true)]).unwrap();
+        assert_eq!(c, b);
+        complete(&queue, a, 0);
+        complete(&queue, c, 0);
+        assert_eq!(queue.pop_used(), Some((a, 0)));
+        assert_eq!(queue.pop_used(), Some((c, 0)));
+        assert_eq!(queue.num_free(), 4);
+    }
+
+    #[test]
+    fn test_rejects_bad_size() {
+        let mut mem = alloc::vec![0u64; 64];
+        let base = mem.as_mut_ptr() as *mut u8;
+        assert!(unsafe { Virtqueue::from_raw(0, 3, base, 0) }.is_err());
+        assert!(unsafe { Virtqueue::from_raw(0, 0, base, 0) }.is_err());
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtio Transports
+//!
+//! A transport gives drivers uniform access to a virtio device's status,
+//! feature bits, queues and configuration space regardless of whether it
+//! sits on PCI or behind an MMIO window. The helpers here implement the
+//! initialization sequence from the virtio 1.0 specification (3.1.1).
+
+use libddk::{Bti, Interrupt};
+use libsys::{Error, Result, Status};
+
+use crate::queue::{Virtqueue, MAX_QUEUE_SIZE};
+
+/// Device status bits
+pub mod status {
+    pub const ACKNOWLEDGE: u8 = 0x01;
+    pub const DRIVER: u8 = 0x02;
+    pub const DRIVER_OK: u8 = 0x04;
+    pub const FEATURES_OK: u8 = 0x08;
+    pub const NEEDS_RESET: u8 = 0x40;
+    pub const FAILED: u8 = 0x80;
+}
+
+/// Device-independent feature bits
+pub mod features {
+    /// Device complies with virtio 1.0 or later
+    pub const VERSION_1: u64 = 1 << 32;
+}
+
+/// Virtio device types
+pub mod device_type {
+    pub const NET: u32 = 1;
+    pub const BLOCK: u32 = 2;
+    pub const CONSOLE: u32 = 3;
+    pub const ENTROPY: u32 = 4;
+    pub const BALLOON: u32 = 5;
+    pub const SCSI: u32 = 8;
+    pub const P9: u32 = 9;
+    pub const GPU: u32 = 16;
+    pub const INPUT: u32 = 18;
+}
+
+/// Access to a virtio device
+pub trait Transport {
+    /// Virtio device type
+    fn device_type(&self) -> u32;
+
+    /// Features offered by the device
+    fn device_features(&mut self) -> u64;
+
+    /// Report the features the driver accepted
+    fn set_driver_features(&mut self, features: u64);
+
+    /// Current device status
+    fn status(&self) -> u8;
+
+    /// Write the device status (0 resets the device)
+    fn set_status(&mut self, status: u8);
+
+    /// Largest size the device supports for queue `index` (0 = absent)
+    fn max_queue_size(&mut self, index: u16) -> u16;
+
+    /// Hand a queue's rings to the device and enable it
+    fn enable_queue(&mut self, queue: &Virtqueue) -> Result<()>;
+
+    /// Tell the device queue `index` has new buffers
+    fn notify(&mut self, index: u16);
+
+    /// Read and clear the interrupt cause; true if the device interrupted
+    fn ack_interrupt(&mut self) -> bool;
+
+    /// Interrupt object for the device
+    fn interrupt(&self) -> &Interrupt;
+
+    /// Read a byte of device configuration space
+    fn config_read8(&self, offset: usize) -> u8;
+
+    /// Read a word of device configuration space
+    fn config_read16(&self, offset: usize) -> u16;
+
+    /// Read a dword of device configuration space
+    fn config_read32(&self, offset: usize) -> u32;
+
+    /// Read a 64-bit field of device configuration space
+    fn config_read64(&self, offset: usize) -> u64 {
+        self.config_read32(offset) as u64 | (self.config_read32(offset + 4) as u64) << 32
+    }
+}
+
+/// Reset the device and negotiate features
+///
+/// Accepts those of `wanted` the device offers, plus `VERSION_1` which
+/// is required. Returns the accepted set.
+pub fn negotiate(transport: &mut dyn Transport, wanted: u64) -> Result<u64> {
+    transport.set_status(0);
+    transport.set_status(status::ACKNOWLEDGE);
+    transport.set_status(status::ACKNOWLEDGE | status::DRIVER);
+
+    let offered = transport.device_features();
+    if offered & features::VERSION_1 == 0 {
+        // Legacy-only device
+        transport.set_status(status::FAILED);
+        return Err(Error::new(Status::NotSupported));
+    }
+
+    let accepted = offered & (wanted | features::VERSION_1);
+    transport.set_driver_features(accepted);
+    transport.set_status(status::ACKNOWLEDGE | status::DRIVER | status::FEATURES_OK);
+    if transport.status() & status::FEATURES_OK == 0 {
+        transport.set_status(status::FAILED);
+        return Err(Error::new(Status::NotSupported));
+    }
+
+    Ok(accepted)
+}
+
+/// Allocate and enable queue `index` with up to `max_size` entries
+pub fn setup_queue(transport: &mut dyn Transport, bti: &Bti, index: u16, max_size: u16) -> Result<Virtqueue> {
+    let limit = transport.max_queue_size(index).min(max_size).min(MAX_QUEUE_SIZE);
+    if limit == 0 {
+        return Err(Error::new(Status::NotFound));
+    }
+    // Split rings index with free-running u16 counters
+    let size = 1u16 << (15 - limit.leading_zeros());
+
+    let queue = Virtqueue::create(bti, index, size)?;
+    transport.enable_queue(&queue)?;
+    Ok(queue)
+}
+
+/// Finish initialization; the device may use its queues afterwards
+pub fn driver_ok(transport: &mut dyn Transport) {
+    let current = transport.status();
+    transport.set_status(current | status::DRIVER_OK);
+}
+
+/// Consume and acknowledge a pending interrupt, if any
+///
+/// Drivers poll their used rings, so this only keeps the interrupt line
+/// from staying asserted.
+pub fn service_interrupt(transport: &mut dyn Transport) {
+    if transport.interrupt().try_wait().is_ok() {
+        transport.ack_interrupt();
+        let _ = transport.interrupt().ack();
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot Sector and FSInfo
+//!
+//! The BIOS parameter block in the boot sector describes the volume's
+//! layout: reserved sectors, then the FATs, then the data clusters
+//! numbered from 2. The FSInfo sector caches the free cluster count and
+//! where to look for the next free cluster; both are hints that may be
+//! unknown or stale.
+//!
+//! A volume is taken as FAT32 when its BPB has the FAT32 layout (no
+//! fixed root directory, no 16-bit FAT size), whatever its cluster
+//! count. Linux does the same, and `mkfs.vfat -F 32` makes volumes too
+//! small to be FAT32 by cluster count that way.
+
+use libsys::{Error, Result, Status};
+
+/// First cluster of the data area
+pub const FIRST_CLUSTER: u32 = 2;
+
+/// FSInfo signatures at offsets 0, 484 and 508
+const FSINFO_LEAD: u32 = 0x4161_5252;
+const FSINFO_STRUCT: u32 = 0x6141_7272;
+const FSINFO_TRAIL: u32 = 0xAA55_0000;
+
+/// FSInfo value for an unknown count or cluster
+const FSINFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// `ext_flags` bit set when only one FAT is active
+const NO_MIRRORING: u16 = 1 << 7;
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// What the boot sector says about a FAT32 volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootSector {
+    pub bytes_per_sector: u32,
+    pub sectors_per_cluster: u32,
+    pub reserved_sectors: u32,
+    pub fat_count: u32,
+    pub media: u8,
+    pub total_sectors: u32,
+
+    /// Sectors in each FAT
+    pub fat_sectors: u32,
+
+    /// The only FAT in use, if the FATs aren't mirrored
+    pub active_fat: Option<u32>,
+
+    pub root_cluster: u32,
+    pub fsinfo_sector: Option<u32>,
+    pub backup_sector: Option<u32>,
+    pub volume_id: u32,
+
+    /// Volume label, space padded; the root directory's label entry
+    /// takes precedence
+    pub label: [u8; 11],
+}
+
+impl BootSector {
+    /// Parse a boot sector
+    ///
+    /// Fails with `NotSupported` if it doesn't describe a FAT32 volume.
+    pub fn parse(sector: &[u8]) -> Result<Self> {
+        let unsupported = Error::new(Status::NotSupported);
+        if sector.len() < 512 || sector[510] != 0x55 || sector[511] != 0xAA {
+            return Err(unsupported);
+        }
+
+        let bytes_per_sector = u16_at(sector, 11) as u32;
+        let sectors_per_cluster = sector[13] as u32;
+        let root_entries = u16_at(sector, 17);
+        let fat_sectors_16 = u16_at(sector, 22);
+        let ext_flags = u16_at(sector, 40);
+        let optional = |value: u16| match value {
+            0 | 0xFFFF => None,
+            sector => Some(sector as u32),
+        };
+
+        let bpb = Self {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors: u16_at(sector, 14) as u32,
+            fat_count: sector[16] as u32,
+            media: sector[21],
+            total_sectors: match u16_at(sector, 19) {
+                0 => u32_at(sector, 32),
+                total => total as u32,
+            },
+            fat_sectors: u32_at(sector, 36),
+            active_fat: (ext_flags & NO_MIRRORING != 0).then_some((ext_flags & 0xF) as u32),
+            root_cluster: u32_at(sector, 44),
+            fsinfo_sector: optional(u16_at(sector, 48)),
+            backup_sector: optional(u16_at(sector, 50)),
+            volume_id: u32_at(sector, 67),
+            label: sector[71..82].try_into().unwrap(),
+        };
+
+        let valid = (512..=4096).contains(&bytes_per_sector)
+            && bytes_per_sector.is_power_of_two()
+            && sectors_per_cluster.is_power_of_two()
+            && bpb.cluster_size() <= 64 * 1024
+            && bpb.reserved_sectors > 0
+            && bpb.fat_count > 0
+            && bpb.active_fat.is_none_or(|fat| fat < bpb.fat_count)
+            && root_entries == 0
+            && fat_sectors_16 == 0
+            && bpb.fat_sectors > 0
+            && bpb.data_start() < bpb.total_sectors
+            && bpb.cluster_count() > 0
+            && (FIRST_CLUSTER..FIRST_CLUSTER + bpb.cluster_count()).contains(&bpb.root_cluster);
+        if !valid {
+            return Err(unsupported);
+        }
+        Ok(bpb)
+    }
+
+    /// Bytes in a cluster
+    pub fn cluster_size(&self) -> u32 {
+        self.bytes_per_sector * self.sectors_per_cluster
+    }
+
+    /// First sector of FAT `copy`
+    pub fn fat_start(&self, copy: u32) -> u32 {
+        self.reserved_sectors + copy * self.fat_sectors
+    }
+
+    /// First sector of the data area
+    pub fn data_start(&self) -> u32 {
+        self.fat_start(self.fat_count)
+    }
+
+    /// Data clusters, limited by both the data area and the FAT size
+    pub fn cluster_count(&self) -> u32 {
+        let by_data = (self.total_sectors.saturating_sub(self.data_start())) / self.sectors_per_cluster;
+        let by_fat = (self.fat_sectors * (self.bytes_per_sector / 4)).saturating_sub(FIRST_CLUSTER);
+        by_data.min(by_fat).min(0x0FFF_FFF5)
+    }
+
+    /// First sector of data cluster `cluster`
+    pub fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.data_start() + (cluster - FIRST_CLUSTER) * self.sectors_per_cluster
+    }
+}
+
+/// The hints an FSInfo sector holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsInfo {
+    pub free_clusters: Option<u32>,
+    pub next_free: Option<u32>,
+}
+
+impl FsInfo {
+    /// Parse an FSInfo sector, if it has the FSInfo signatures
+    pub fn parse(sector: &[u8]) -> Option<Self> {
+        if sector.len() < 512
+            || u32_at(sector, 0) != FSINFO_LEAD
+            || u32_at(sector, 484) != FSINFO_STRUCT
+            || u32_at(sector, 508) != FSINFO_TRAIL
+        {
+            return None;
+        }
+        let known = |value: u32| (value != FSINFO_UNKNOWN).then_some(value);
+        Some(Self {
+            free_clusters: known(u32_at(sector, 488)),
+            next_free: known(u32_at(sector, 492)),
+        })
+    }
+
+    /// Store the hints in an FSInfo sector, leaving the rest of it alone
+    pub fn store(&self, sector: &mut [u8]) {
+        let free = self.free_clusters.unwrap_or(FSINFO_UNKNOWN);
+        let next = self.next_free.unwrap_or(FSINFO_UNKNOWN);
+        sector[488..492].copy_from_slice(&free.to_le_bytes());
+        sector[492..496].copy_from_slice(&next.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image;
+
+    #[test]
+    fn test_parse_boot_sector() {
+        let sector = image::boot_sector(image::SECTORS, 1);
+        let bpb = BootSector::parse(&sector).unwrap();
+        assert_eq!(bpb.bytes_per_sector, 512);
+        assert_eq!(bpb.reserved_sectors, 32);
+        assert_eq!(bpb.fat_count, 2);
+        assert_eq!(bpb.active_fat, None);
+        assert_eq!(bpb.root_cluster, 2);
+        assert_eq!(bpb.fsinfo_sector, Some(1));
+        assert_eq!(bpb.backup_sector, Some(6));
+        assert_eq!(&bpb.label, b"NO NAME    ");
+        assert_eq!(bpb.data_start(), 32 + 2 * bpb.fat_sectors);
+        assert_eq!(bpb.cluster_sector(2), bpb.data_start());
+
+        // Every cluster has a FAT entry
+        assert!(bpb.fat_sectors * 128 >= bpb.cluster_count() + 2);
+
+        // A FAT16 BPB has a root directory and a 16-bit FAT size
+        let mut fat16 = sector;
+        fat16[17..19].copy_from_slice(&512u16.to_le_bytes());
+        fat16[22..24].copy_from_slice(&32u16.to_le_bytes());
+        assert_eq!(BootSector::parse(&fat16).unwrap_err().status(), Status::NotSupported);
+
+        let mut unsigned = sector;
+        unsigned[510] = 0;
+        assert!(BootSector::parse(&unsigned).is_err());
+    }
+
+    #[test]
+    fn test_fsinfo() {
+        let mut sector = image::fsinfo_sector(1000, 3);
+        assert_eq!(
+            FsInfo::parse(&sector),
+            Some(FsInfo {
+                free_clusters: Some(1000),
+                next_free: Some(3)
+            })
+        );
+
+        FsInfo {
+            free_clusters: None,
+            next_free: Some(9),
+        }
+        .store(&mut sector);
+        assert_eq!(
+            FsInfo::parse(&sector),
+            Some(FsInfo {
+                free_clusters: None,
+                next_free: Some(9)
+            })
+        );
+
+        sector[0] = 0;
+        assert_eq!(FsInfo::parse(&sector), None);
+    }
+}
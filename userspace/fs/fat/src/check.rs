@@ -0,0 +1,391 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Consistency Checks
+//!
+//! A light `fsck`: walk the directory tree from the root, follow every
+//! chain, and compare what was found with the FAT, its copies, the
+//! FSInfo hints and the backup boot sector. Problems are reported, not
+//! repaired.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use libddk::BlockDevice;
+use libsys::Result;
+
+use crate::bpb::FsInfo;
+use crate::dir;
+use crate::table::{self, Fat, BAD, FREE};
+use crate::Volume;
+
+/// Something wrong with a volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A chain runs into a free, bad or out of range cluster
+    BrokenChain { path: String, cluster: u32 },
+
+    /// A chain runs into a cluster already claimed, by another node or by
+    /// itself
+    CrossLinked { path: String, cluster: u32 },
+
+    /// A file's chain doesn't have the clusters its size needs
+    SizeMismatch { path: String, size: u32, clusters: u32 },
+
+    /// A directory's `.` or `..` is missing or points elsewhere
+    BadDotEntries { path: String },
+
+    /// Long name entries that belong to no short entry
+    OrphanLongNames { path: String, count: u32 },
+
+    /// Clusters in use that no node owns
+    LostClusters { count: u32 },
+
+    /// The FSInfo free cluster count is wrong
+    FreeCountMismatch { recorded: u32, actual: u32 },
+
+    /// A FAT copy differs from the first
+    FatCopiesDiffer { copy: u32 },
+
+    /// The first FAT entry doesn't hold the media byte
+    BadMediaEntry,
+
+    /// The backup boot sector differs from the boot sector
+    BackupBootSectorDiffers,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::BrokenChain { path, cluster } => write!(f, "{}: broken chain at cluster {}", path, cluster),
+            Problem::CrossLinked { path, cluster } => write!(f, "{}: cross-linked at cluster {}", path, cluster),
+            Problem::SizeMismatch { path, size, clusters } => {
+                write!(f, "{}: size {} doesn't match {} clusters", path, size, clusters)
+            }
+            Problem::BadDotEntries { path } => write!(f, "{}: bad . or .. entry", path),
+            Problem::OrphanLongNames { path, count } => write!(f, "{}: {} orphaned long name entries", path, count),
+            Problem::LostClusters { count } => write!(f, "{} lost clusters", count),
+            Problem::FreeCountMismatch { recorded, actual } => {
+                write!(f, "FSInfo free count {}, actually {}", recorded, actual)
+            }
+            Problem::FatCopiesDiffer { copy } => write!(f, "FAT copy {} differs from the first", copy),
+            Problem::BadMediaEntry => write!(f, "first FAT entry doesn't match the media byte"),
+            Problem::BackupBootSectorDiffers => write!(f, "backup boot sector differs"),
+        }
+    }
+}
+
+/// What a check found
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+    pub files: u32,
+    pub directories: u32,
+
+    /// Clusters owned by files and directories
+    pub used_clusters: u32,
+    pub free_clusters: u32,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Follow the chain from `first`, claiming its clusters for `path`
+///
+/// Returns the clusters up to the first problem.
+fn claim(fat: &Fat, owned: &mut [bool], first: u32, path: &str, problems: &mut Vec<Problem>) -> Vec<u32> {
+    let mut clusters = Vec::new();
+    let mut cluster = first;
+    loop {
+        if !fat.contains(cluster) {
+            problems.push(Problem::BrokenChain {
+                path: String::from(path),
+                cluster,
+            });
+            break;
+        }
+        if owned[cluster as usize] {
+            problems.push(Problem::CrossLinked {
+                path: String::from(path),
+                cluster,
+            });
+            break;
+        }
+        owned[cluster as usize] = true;
+        clusters.push(cluster);
+
+        match fat.get(cluster) {
+            entry if table::is_end(entry) => break,
+            entry if fat.contains(entry) && entry != FREE => cluster = entry,
+            _ => {
+                problems.push(Problem::BrokenChain {
+                    path: String::from(path),
+                    cluster,
+                });
+                break;
+            }
+        }
+    }
+    clusters
+}
+
+fn join(parent: &str, name: &str) -> String {
+    let mut path = String::from(parent);
+    if !path.ends_with('/') {
+        path.push('/');
+    }
+    path.push_str(name);
+    path
+}
+
+/// Check a volume
+///
+/// Syncs it first, so what is checked is what is on the device.
+pub fn check<D: BlockDevice>(volume: &mut Volume<D>) -> Result<Report> {
+    volume.sync()?;
+    let bpb = *volume.boot_sector();
+    let sector_size = bpb.bytes_per_sector as usize;
+    let cluster_size = bpb.cluster_size();
+    let mut report = Report::default();
+
+    let mut boot = vec![0u8; sector_size];
+    let mut sector = vec![0u8; sector_size];
+    volume.device_mut().read_blocks(0, &mut boot)?;
+    if let Some(backup) = bpb.backup_sector {
+        volume.device_mut().read_blocks(backup as u64, &mut sector)?;
+        if sector != boot {
+            report.problems.push(Problem::BackupBootSectorDiffers);
+        }
+    }
+
+    let entries = volume.fat().len() as usize;
+    if volume.fat().get(0) & 0xFF != bpb.media as u32 {
+        report.problems.push(Problem::BadMediaEntry);
+    }
+    if bpb.active_fat.is_none() {
+        let first = table::read_copy(volume.device_mut(), &bpb, 0, entries)?;
+        for copy in 1..bpb.fat_count {
+            if table::read_copy(volume.device_mut(), &bpb, copy, entries)? != first {
+                report.problems.push(Problem::FatCopiesDiffer { copy });
+            }
+        }
+    }
+
+    // Directories to visit: first cluster, parent's first cluster as
+    // `..` gives it, and path
+    let mut owned = vec![false; entries];
+    let mut pending = vec![(bpb.root_cluster, None, String::from("/"))];
+    while let Some((cluster, parent, path)) = pending.pop() {
+        let clusters = claim(volume.fat(), &mut owned, cluster, &path, &mut report.problems);
+        let data = volume.read_clusters(&clusters)?;
+        let listing = dir::parse(&data);
+
+        if listing.orphans > 0 {
+            report.problems.push(Problem::OrphanLongNames {
+                path: path.clone(),
+                count: listing.orphans,
+            });
+        }
+        if let Some(parent) = parent {
+            if listing.dot != Some(cluster) || listing.dotdot != Some(parent) {
+                report.problems.push(Problem::BadDotEntries { path: path.clone() });
+            }
+        }
+
+        let here = if parent.is_none() { 0 } else { cluster };
+        for item in &listing.items {
+            let child = join(&path, &item.name);
+            let first = item.entry.cluster();
+            if item.entry.is_dir() {
+                report.directories += 1;
+                if !volume.fat().contains(first) {
+                    report.problems.push(Problem::BrokenChain {
+                        path: child,
+                        cluster: first,
+                    });
+                } else if owned[first as usize] {
+                    report.problems.push(Problem::CrossLinked {
+                        path: child,
+                        cluster: first,
+                    });
+                } else {
+                    pending.push((first, Some(here), child));
+                }
+                continue;
+            }
+
+            report.files += 1;
+            let count = match first {
+                0 => 0,
+                first => claim(volume.fat(), &mut owned, first, &child, &mut report.problems).len() as u32,
+            };
+            let size = item.entry.size();
+            if count != size.div_ceil(cluster_size) {
+                report.problems.push(Problem::SizeMismatch {
+                    path: child,
+                    size,
+                    clusters: count,
+                });
+            }
+        }
+    }
+
+    let fat = volume.fat();
+    let lost = (2..fat.len())
+        .filter(|&cluster| !owned[cluster as usize] && fat.get(cluster) != FREE && fat.get(cluster) != BAD)
+        .count() as u32;
+    if lost > 0 {
+        report.problems.push(Problem::LostClusters { count: lost });
+    }
+    report.used_clusters = owned.iter().filter(|&&owned| owned).count() as u32;
+    report.free_clusters = fat.free_count();
+
+    if let Some(index) = bpb.fsinfo_sector {
+        volume.device_mut().read_blocks(index as u64, &mut sector)?;
+        let recorded = FsInfo::parse(&sector).and_then(|fsinfo| fsinfo.free_clusters);
+        if let Some(recorded) = recorded.filter(|&recorded| recorded != report.free_clusters) {
+            report.problems.push(Problem::FreeCountMismatch {
+                recorded,
+                actual: report.free_clusters,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{self, RamDisk};
+    use crate::volume::Node;
+
+    /// Byte offset of a node's entry on the disk
+    fn entry_offset(volume: &Volume<RamDisk>, node: &Node) -> usize {
+        let location = node.location.unwrap();
+        let size = volume.cluster_size() as usize;
+        let chain = volume.fat().chain(location.dir).unwrap();
+        let cluster = chain[location.offset as usize / size];
+        volume.boot_sector().cluster_sector(cluster) as usize * 512 + location.offset as usize % size
+    }
+
+    /// A volume with a few directories and files
+    fn populated() -> Volume<RamDisk> {
+        let mut volume = image::mount(1);
+        let root = volume.root();
+        let efi = volume.create(&root, "EFI", true).unwrap();
+        let boot = volume.create(&efi, "BOOT", true).unwrap();
+        let mut loader = volume.create(&boot, "BOOTX64.EFI", false).unwrap();
+        volume.write(&mut loader, 0, &[0xAA; 2000]).unwrap();
+        let mut notes = volume.create(&root, "Release notes.txt", false).unwrap();
+        volume.write(&mut notes, 0, b"notes").unwrap();
+        volume.create(&root, "empty", false).unwrap();
+        volume
+    }
+
+    #[test]
+    fn test_clean() {
+        let mut volume = image::mount(1);
+        let report = check(&mut volume).unwrap();
+        assert!(report.is_clean(), "{:?}", report.problems);
+        assert_eq!(report.used_clusters, 1);
+
+        let mut volume = populated();
+        let report = check(&mut volume).unwrap();
+        assert!(report.is_clean(), "{:?}", report.problems);
+        assert_eq!((report.files, report.directories), (3, 2));
+        assert_eq!(report.used_clusters, 1 + 2 + 4 + 1);
+        assert_eq!(report.free_clusters, volume.fat().free_count());
+
+        // Still clean once things are removed again
+        let root = volume.root();
+        volume.remove(&root, "Release notes.txt").unwrap();
+        let efi = volume.lookup(&root, "EFI").unwrap();
+        volume.rename(&efi, "BOOT", &root, "boot").unwrap();
+        assert!(check(&mut volume).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_problems() {
+        let mut volume = populated();
+        let root = volume.root();
+        let notes = volume.lookup(&root, "Release notes.txt").unwrap();
+        let empty = volume.lookup(&root, "empty").unwrap();
+        let boot = volume.resolve(&["EFI", "BOOT"]).unwrap();
+        let loader = volume.lookup(&boot, "BOOTX64.EFI").unwrap();
+        let notes_at = entry_offset(&volume, &notes);
+        let empty_at = entry_offset(&volume, &empty);
+        let boot_dir = volume.boot_sector().cluster_sector(boot.cluster()) as usize * 512;
+        let bpb = *volume.boot_sector();
+        volume.sync().unwrap();
+        let mut disk = volume.into_device();
+
+        // "empty" shares the loader's clusters; the notes' size is wrong
+        let mut entry = empty.entry;
+        entry.set_cluster(loader.cluster());
+        entry.set_size(2000);
+        disk.0[empty_at..empty_at + 32].copy_from_slice(&entry.0);
+        disk.0[notes_at + 28..notes_at + 32].copy_from_slice(&5000u32.to_le_bytes());
+
+        // The notes' chain runs into a free cluster, their long name loses
+        // its checksum
+        let fat = bpb.fat_start(0) as usize * 512;
+        let link = fat + notes.cluster() as usize * 4;
+        disk.0[link..link + 4].copy_from_slice(&table::FREE.to_le_bytes());
+        disk.0[notes_at - 32 + 13] ^= 0xFF;
+
+        // BOOT's `..` points at the root instead of EFI
+        disk.0[boot_dir + 32 + 26..boot_dir + 32 + 28].copy_from_slice(&0u16.to_le_bytes());
+
+        // A lost cluster, in the first FAT only, and a stale FSInfo
+        disk.0[fat + 4000..fat + 4004].copy_from_slice(&table::END_OF_CHAIN.to_le_bytes());
+        disk.0[512 + 488..512 + 492].copy_from_slice(&7u32.to_le_bytes());
+        disk.0[6 * 512 + 67] ^= 1;
+
+        let mut volume = Volume::mount(disk).unwrap();
+        let problems = check(&mut volume).unwrap().problems;
+        let expected = [
+            Problem::BackupBootSectorDiffers,
+            Problem::FatCopiesDiffer { copy: 1 },
+            Problem::OrphanLongNames {
+                path: String::from("/"),
+                count: 2,
+            },
+            Problem::BrokenChain {
+                path: String::from("/RELEAS~1.TXT"),
+                cluster: notes.cluster(),
+            },
+            Problem::SizeMismatch {
+                path: String::from("/RELEAS~1.TXT"),
+                size: 5000,
+                clusters: 1,
+            },
+            Problem::BadDotEntries {
+                path: String::from("/EFI/BOOT"),
+            },
+            Problem::CrossLinked {
+                path: String::from("/EFI/BOOT/BOOTX64.EFI"),
+                cluster: loader.cluster(),
+            },
+            Problem::SizeMismatch {
+                path: String::from("/EFI/BOOT/BOOTX64.EFI"),
+                size: 2000,
+                clusters: 0,
+            },
+            Problem::LostClusters { count: 1 },
+        ];
+        for problem in &expected {
+            assert!(problems.contains(problem), "{} not in {:?}", problem, problems);
+        }
+        assert!(problems
+            .iter()
+            .any(|p| matches!(p, Problem::FreeCountMismatch { recorded: 7, .. })));
+    }
+}
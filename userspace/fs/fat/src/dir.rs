@@ -0,0 +1,623 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Directory Entries
+//!
+//! A directory is an array of 32-byte entries. Every file has a short
+//! entry with an 8.3 name, its first cluster and its size; a name that
+//! doesn't fit 8.3 is stored in long name entries just before it, last
+//! part first, each holding 13 UTF-16 characters and the checksum of the
+//! short name they belong to.
+//!
+//! 8.3 names that are all lower case in the base or the extension are
+//! stored upper case with the case bits Windows NT and Linux use, rather
+//! than with a long name.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Size of a directory entry
+pub const ENTRY_SIZE: usize = 32;
+
+/// Attribute bits
+pub mod attr {
+    pub const READ_ONLY: u8 = 0x01;
+    pub const HIDDEN: u8 = 0x02;
+    pub const SYSTEM: u8 = 0x04;
+    pub const VOLUME_ID: u8 = 0x08;
+    pub const DIRECTORY: u8 = 0x10;
+    pub const ARCHIVE: u8 = 0x20;
+
+    /// All four low bits mark a long name entry
+    pub const LONG_NAME: u8 = 0x0F;
+}
+
+/// First name byte of a free entry
+pub const DELETED: u8 = 0xE5;
+
+/// First name byte of the free entry that ends the directory
+pub const END: u8 = 0x00;
+
+/// First name byte standing for a name that starts with 0xE5
+const ESCAPED_DELETED: u8 = 0x05;
+
+/// Ordinal bit of the last long name entry of a name
+const LAST_LONG_ENTRY: u8 = 0x40;
+
+/// Name characters in one long name entry
+const LONG_NAME_CHARS: usize = 13;
+
+/// Most UTF-16 characters in a long name
+const MAX_LONG_NAME: usize = 255;
+
+/// Case bits: the base or extension of the 8.3 name is lower case
+const LOWER_BASE: u8 = 0x08;
+const LOWER_EXT: u8 = 0x10;
+
+/// Names of `.` and `..`
+pub const DOT: [u8; 11] = *b".          ";
+pub const DOTDOT: [u8; 11] = *b"..         ";
+
+/// Byte offsets of the characters in a long name entry
+fn long_name_offsets() -> impl Iterator<Item = usize> {
+    (1..11).step_by(2).chain((14..26).step_by(2)).chain((28..32).step_by(2))
+}
+
+/// Checksum tying long name entries to their short entry
+pub fn checksum(short: &[u8; 11]) -> u8 {
+    short.iter().fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// A short entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortEntry(pub [u8; ENTRY_SIZE]);
+
+impl ShortEntry {
+    /// An entry stamped with `now`, in seconds since the Unix epoch
+    pub fn new(name: [u8; 11], case: u8, attributes: u8, cluster: u32, size: u32, now: u64) -> Self {
+        let mut entry = Self([0u8; ENTRY_SIZE]);
+        entry.0[0..11].copy_from_slice(&name);
+        entry.0[11] = attributes;
+        entry.0[12] = case;
+        let (date, time) = fat_time(now);
+        entry.0[14..16].copy_from_slice(&time.to_le_bytes());
+        entry.0[16..18].copy_from_slice(&date.to_le_bytes());
+        entry.set_cluster(cluster);
+        entry.set_size(size);
+        entry.set_modified(now);
+        entry
+    }
+
+    pub fn name(&self) -> [u8; 11] {
+        self.0[0..11].try_into().unwrap()
+    }
+
+    /// Give the entry another 8.3 name and case bits
+    pub fn set_name(&mut self, name: [u8; 11], case: u8) {
+        self.0[0..11].copy_from_slice(&name);
+        self.0[12] = case;
+    }
+
+    pub fn attributes(&self) -> u8 {
+        self.0[11]
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes() & attr::DIRECTORY != 0
+    }
+
+    pub fn cluster(&self) -> u32 {
+        let high = u16::from_le_bytes([self.0[20], self.0[21]]) as u32;
+        let low = u16::from_le_bytes([self.0[26], self.0[27]]) as u32;
+        high << 16 | low
+    }
+
+    pub fn set_cluster(&mut self, cluster: u32) {
+        self.0[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        self.0[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    }
+
+    pub fn size(&self) -> u32 {
+        u32::from_le_bytes(self.0[28..32].try_into().unwrap())
+    }
+
+    pub fn set_size(&mut self, size: u32) {
+        self.0[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// Last write, in seconds since the Unix epoch (0 if unset)
+    pub fn modified(&self) -> u64 {
+        let time = u16::from_le_bytes([self.0[22], self.0[23]]);
+        let date = u16::from_le_bytes([self.0[24], self.0[25]]);
+        unix_time(date, time)
+    }
+
+    /// Record a write at `now`, which also marks the entry for backup
+    pub fn set_modified(&mut self, now: u64) {
+        let (date, time) = fat_time(now);
+        self.0[11] |= if self.is_dir() { 0 } else { attr::ARCHIVE };
+        self.0[18..20].copy_from_slice(&date.to_le_bytes());
+        self.0[22..24].copy_from_slice(&time.to_le_bytes());
+        self.0[24..26].copy_from_slice(&date.to_le_bytes());
+    }
+
+    /// The 8.3 name as text, such as `README.TXT`
+    pub fn short_name(&self) -> String {
+        let mut name = self.name();
+        if name[0] == ESCAPED_DELETED {
+            name[0] = DELETED;
+        }
+        // OEM code page bytes are taken as Latin-1
+        let case = self.0[12];
+        let part = |bytes: &[u8], lower: bool| {
+            bytes
+                .iter()
+                .map(move |&b| if lower { b.to_ascii_lowercase() } else { b } as char)
+                .collect::<String>()
+        };
+
+        let mut text = part(trim_spaces(&name[..8]), case & LOWER_BASE != 0);
+        let ext = trim_spaces(&name[8..]);
+        if !ext.is_empty() {
+            text.push('.');
+            text.push_str(&part(ext, case & LOWER_EXT != 0));
+        }
+        text
+    }
+}
+
+fn trim_spaces(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+    &bytes[..len]
+}
+
+/// A file or directory found in a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// Long name, or the 8.3 name if there is none
+    pub name: String,
+
+    pub entry: ShortEntry,
+
+    /// Byte offset of the short entry in the directory
+    pub offset: u32,
+
+    /// Byte offset of the first entry of the item, its first long name
+    /// entry if it has any
+    pub first_slot: u32,
+}
+
+impl Item {
+    /// Whether `name` names this item, by its long or its 8.3 name
+    pub fn matches(&self, name: &str) -> bool {
+        same_name(&self.name, name) || same_name(&self.entry.short_name(), name)
+    }
+}
+
+/// What a directory holds
+#[derive(Debug, Default)]
+pub struct Listing {
+    pub items: Vec<Item>,
+
+    /// Clusters `.` and `..` point at, if the directory has them
+    pub dot: Option<u32>,
+    pub dotdot: Option<u32>,
+
+    /// The volume label, found in the root directory
+    pub label: Option<[u8; 11]>,
+
+    /// Long name entries that belong to no short entry
+    pub orphans: u32,
+}
+
+/// Long name being collected
+struct LongName {
+    chars: Vec<u16>,
+    checksum: u8,
+
+    /// Ordinal of the next entry expected
+    next: u8,
+
+    first_slot: u32,
+    slots: u32,
+}
+
+/// Read the entries of a directory's data
+pub fn parse(data: &[u8]) -> Listing {
+    let mut listing = Listing::default();
+    let mut long: Option<LongName> = None;
+
+    for (index, raw) in data.chunks_exact(ENTRY_SIZE).enumerate() {
+        let offset = (index * ENTRY_SIZE) as u32;
+        match raw[0] {
+            END => break,
+            DELETED => {
+                listing.orphans += long.take().map_or(0, |l| l.slots);
+                continue;
+            }
+            _ => {}
+        }
+
+        if raw[11] & 0x3F == attr::LONG_NAME {
+            let ordinal = raw[0] & !LAST_LONG_ENTRY;
+            let count = ordinal as usize;
+            if raw[0] & LAST_LONG_ENTRY != 0 {
+                listing.orphans += long.take().map_or(0, |l| l.slots);
+                if count == 0 || count * LONG_NAME_CHARS > MAX_LONG_NAME + LONG_NAME_CHARS {
+                    listing.orphans += 1;
+                    continue;
+                }
+                long = Some(LongName {
+                    chars: alloc::vec![0xFFFF; count * LONG_NAME_CHARS],
+                    checksum: raw[13],
+                    next: ordinal,
+                    first_slot: offset,
+                    slots: 0,
+                });
+            }
+
+            match long.as_mut() {
+                Some(name) if name.next == ordinal && ordinal > 0 && name.checksum == raw[13] => {
+                    let start = (count - 1) * LONG_NAME_CHARS;
+                    for (i, at) in long_name_offsets().enumerate() {
+                        name.chars[start + i] = u16::from_le_bytes([raw[at], raw[at + 1]]);
+                    }
+                    name.next -= 1;
+                    name.slots += 1;
+                }
+                _ => {
+                    listing.orphans += long.take().map_or(0, |l| l.slots) + 1;
+                }
+            }
+            continue;
+        }
+
+        let entry = ShortEntry(raw.try_into().unwrap());
+        let short = entry.name();
+        if entry.attributes() & attr::VOLUME_ID != 0 {
+            listing.orphans += long.take().map_or(0, |l| l.slots);
+            listing.label.get_or_insert(short);
+            continue;
+        }
+        if short == DOT || short == DOTDOT {
+            listing.orphans += long.take().map_or(0, |l| l.slots);
+            if short == DOT {
+                listing.dot = Some(entry.cluster());
+            } else {
+                listing.dotdot = Some(entry.cluster());
+            }
+            continue;
+        }
+
+        let (name, first_slot) = match long.take() {
+            Some(long) if long.next == 0 && long.checksum == checksum(&short) => {
+                let len = long
+                    .chars
+                    .iter()
+                    .position(|&c| c == 0 || c == 0xFFFF)
+                    .unwrap_or(long.chars.len());
+                let name = char::decode_utf16(long.chars[..len].iter().copied())
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect();
+                (name, long.first_slot)
+            }
+            other => {
+                listing.orphans += other.map_or(0, |l| l.slots);
+                (entry.short_name(), offset)
+            }
+        };
+        listing.items.push(Item {
+            name,
+            entry,
+            offset,
+            first_slot,
+        });
+    }
+    listing.orphans += long.map_or(0, |l| l.slots);
+    listing
+}
+
+/// Long name entries for `name`, in the order they go on disk
+pub fn long_entries(name: &str, checksum: u8) -> Vec<[u8; ENTRY_SIZE]> {
+    let chars: Vec<u16> = name.encode_utf16().collect();
+    let parts = chars.len().div_ceil(LONG_NAME_CHARS);
+
+    (0..parts)
+        .rev()
+        .map(|part| {
+            let mut entry = [0u8; ENTRY_SIZE];
+            entry[0] = (part + 1) as u8 | if part + 1 == parts { LAST_LONG_ENTRY } else { 0 };
+            entry[11] = attr::LONG_NAME;
+            entry[13] = checksum;
+            // The name ends with a NUL if it fits, then 0xFFFF padding
+            for (i, at) in long_name_offsets().enumerate() {
+                let index = part * LONG_NAME_CHARS + i;
+                let c = match index.cmp(&chars.len()) {
+                    core::cmp::Ordering::Less => chars[index],
+                    core::cmp::Ordering::Equal => 0,
+                    core::cmp::Ordering::Greater => 0xFFFF,
+                };
+                entry[at..at + 2].copy_from_slice(&c.to_le_bytes());
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Whether `name` can be given to a file
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.encode_utf16().count() <= MAX_LONG_NAME
+        && !name.ends_with(['.', ' '])
+        && !name.chars().any(|c| c < ' ' || "\"*/:<>?\\|".contains(c))
+}
+
+/// Whether `c` may appear in an 8.3 name
+fn short_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'()-@^_`{}~".contains(&c)
+}
+
+/// `name` as an 8.3 name and its case bits, if it is one
+///
+/// Base and extension must each be of one case.
+pub fn short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    let mut case = 0;
+    let mut part_case = |part: &str, max: usize, lower: u8| {
+        let bytes = part.as_bytes();
+        if bytes.len() > max || !bytes.iter().all(|&c| short_char(c)) {
+            return false;
+        }
+        let has_lower = bytes.iter().any(u8::is_ascii_lowercase);
+        let has_upper = bytes.iter().any(u8::is_ascii_uppercase);
+        if has_lower {
+            case |= lower;
+        }
+        !(has_lower && has_upper)
+    };
+    if base.is_empty() || !part_case(base, 8, LOWER_BASE) || !part_case(ext, 3, LOWER_EXT) {
+        return None;
+    }
+
+    let mut short = [b' '; 11];
+    for (dst, src) in short.iter_mut().zip(base.bytes()) {
+        *dst = src.to_ascii_uppercase();
+    }
+    for (dst, src) in short[8..].iter_mut().zip(ext.bytes()) {
+        *dst = src.to_ascii_uppercase();
+    }
+    Some((short, case))
+}
+
+/// Short alias `BASE~N.EXT` for a long name, with the lowest `N` that
+/// `taken` doesn't reject
+pub fn short_alias(name: &str, taken: impl Fn(&[u8; 11]) -> bool) -> Option<[u8; 11]> {
+    let name = name.trim_start_matches('.');
+    let (base, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let basis = |part: &str| -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                if c.is_ascii() && short_char(c as u8) {
+                    c.to_ascii_uppercase() as u8
+                } else {
+                    b'_'
+                }
+            })
+            .collect()
+    };
+    let mut base = basis(base);
+    let ext = basis(ext);
+    if base.is_empty() {
+        base.push(b'_');
+    }
+
+    for n in 1..1_000_000u32 {
+        let suffix = alloc::format!("~{}", n);
+        let keep = (8 - suffix.len()).min(base.len());
+        let mut alias = [b' '; 11];
+        alias[..keep].copy_from_slice(&base[..keep]);
+        alias[keep..keep + suffix.len()].copy_from_slice(suffix.as_bytes());
+        for (dst, &src) in alias[8..].iter_mut().zip(ext.iter().take(3)) {
+            *dst = src;
+        }
+        if !taken(&alias) {
+            return Some(alias);
+        }
+    }
+    None
+}
+
+/// Whether two names are the same, ignoring case
+pub fn same_name(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_uppercase)
+        .eq(b.chars().flat_map(char::to_uppercase))
+}
+
+/// Days from 1970-01-01 to a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of a day counted from 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Seconds since the Unix epoch of a FAT date and time; 0 if unset
+pub fn unix_time(date: u16, time: u16) -> u64 {
+    let (year, month, day) = (
+        1980 + (date >> 9) as i64,
+        (date >> 5 & 0xF) as u32,
+        (date & 0x1F) as u32,
+    );
+    if !(1..=12).contains(&month) || day == 0 {
+        return 0;
+    }
+    let seconds = (time >> 11) as u64 * 3600 + (time >> 5 & 0x3F) as u64 * 60 + (time & 0x1F) as u64 * 2;
+    days_from_civil(year, month, day) as u64 * 86_400 + seconds
+}
+
+/// FAT date and time of `now`, clamped to the years FAT can hold
+pub fn fat_time(now: u64) -> (u16, u16) {
+    const FIRST: u64 = 315_532_800; // 1980-01-01
+    let now = now.max(FIRST);
+    let (year, month, day) = civil_from_days((now / 86_400) as i64);
+    if year > 2107 {
+        return (127 << 9 | 12 << 5 | 31, 23 << 11 | 59 << 5 | 29);
+    }
+    let seconds = now % 86_400;
+    let date = ((year - 1980) as u16) << 9 | (month as u16) << 5 | day as u16;
+    let time = ((seconds / 3600) as u16) << 11 | ((seconds / 60 % 60) as u16) << 5 | (seconds % 60 / 2) as u16;
+    (date, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory data made of `entries`, ended by a free entry
+    fn directory(entries: &[[u8; ENTRY_SIZE]]) -> Vec<u8> {
+        let mut data: Vec<u8> = entries.iter().flatten().copied().collect();
+        data.resize(data.len() + ENTRY_SIZE, 0);
+        data
+    }
+
+    #[test]
+    fn test_short_names() {
+        assert_eq!(short_name("README.TXT"), Some((*b"README  TXT", 0)));
+        assert_eq!(short_name("hello.txt"), Some((*b"HELLO   TXT", LOWER_BASE | LOWER_EXT)));
+        assert_eq!(short_name("EFI"), Some((*b"EFI        ", 0)));
+        assert_eq!(short_name("Hello.txt"), None);
+        assert_eq!(short_name("toolongname.txt"), None);
+        assert_eq!(short_name("a.b.c"), None);
+        assert_eq!(short_name("with space"), None);
+
+        let entry = ShortEntry::new(*b"HELLO   TXT", LOWER_BASE, 0, 0, 0, 0);
+        assert_eq!(entry.short_name(), "hello.TXT");
+        let entry = ShortEntry::new(*b"\x05ABC       ", 0, 0, 0, 0, 0);
+        assert_eq!(entry.short_name(), "\u{E5}ABC");
+
+        assert!(valid_name("Long File Name.txt"));
+        assert!(!valid_name("a:b") && !valid_name("dot.") && !valid_name("..") && !valid_name(""));
+    }
+
+    #[test]
+    fn test_short_alias() {
+        let alias = short_alias("Long File Name.txt", |_| false).unwrap();
+        assert_eq!(&alias, b"LONGFI~1TXT");
+
+        // Taken aliases are skipped; wide numbers shorten the base
+        let alias = short_alias("Long File Name.txt", |a| a == b"LONGFI~1TXT").unwrap();
+        assert_eq!(&alias, b"LONGFI~2TXT");
+        let alias = short_alias("archive.tar.gz", |a| a[6] == b'~' && a[7] < b'3').unwrap();
+        assert_eq!(&alias, b"ARCHIV~3GZ ");
+        let alias = short_alias("archive.tar.gz", |a| a[6] == b'~').unwrap();
+        assert_eq!(&alias, b"ARCHI~10GZ ");
+        assert_eq!(&short_alias(".bashrc", |_| false).unwrap(), b"BASHRC~1   ");
+        assert_eq!(&short_alias("été+1.md", |_| false).unwrap(), b"_T__1~1 MD ");
+    }
+
+    #[test]
+    fn test_long_names() {
+        let name = "A long name that needs three entries.txt";
+        let short = *b"ALONGN~1TXT";
+        let mut entries = long_entries(name, checksum(&short));
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0][0], 0x44);
+        assert_eq!(entries[3][0], 0x01);
+        entries.push(ShortEntry::new(short, 0, attr::ARCHIVE, 5, 100, 0).0);
+
+        let listing = parse(&directory(&entries));
+        assert_eq!(listing.orphans, 0);
+        assert_eq!(listing.items.len(), 1);
+        let item = &listing.items[0];
+        assert_eq!(item.name, name);
+        assert_eq!((item.first_slot, item.offset), (0, 4 * 32));
+        assert_eq!((item.entry.cluster(), item.entry.size()), (5, 100));
+        assert!(item.matches("a LONG name that needs three ENTRIES.TXT"));
+        assert!(item.matches("alongn~1.txt"));
+
+        // A checksum that doesn't match leaves the long entries orphaned
+        entries[4][0] = b'B';
+        let listing = parse(&directory(&entries));
+        assert_eq!(listing.orphans, 4);
+        assert_eq!(listing.items[0].name, "BLONGN~1.TXT");
+
+        // As does a missing part
+        entries[4][0] = b'A';
+        entries.remove(1);
+        let listing = parse(&directory(&entries));
+        assert_eq!(listing.orphans, 3);
+    }
+
+    #[test]
+    fn test_special_entries() {
+        let mut label = [b' '; ENTRY_SIZE];
+        label[..11].copy_from_slice(b"RUSTUX     ");
+        label[11] = attr::VOLUME_ID;
+        label[12..].fill(0);
+        let mut deleted = ShortEntry::new(*b"GONE       ", 0, 0, 9, 1, 0).0;
+        deleted[0] = DELETED;
+        let entries = [
+            ShortEntry::new(DOT, 0, attr::DIRECTORY, 7, 0, 0).0,
+            ShortEntry::new(DOTDOT, 0, attr::DIRECTORY, 0, 0, 0).0,
+            label,
+            deleted,
+            ShortEntry::new(*b"KEEP    BIN", 0, 0, 8, 1, 0).0,
+        ];
+        let mut data = directory(&entries);
+        // Entries after the end marker are ignored
+        data.extend_from_slice(&ShortEntry::new(*b"STALE      ", 0, 0, 3, 1, 0).0);
+
+        let listing = parse(&data);
+        assert_eq!((listing.dot, listing.dotdot), (Some(7), Some(0)));
+        assert_eq!(listing.label.as_ref(), Some(b"RUSTUX     "));
+        assert_eq!(listing.items.len(), 1);
+        assert_eq!(listing.items[0].name, "KEEP.BIN");
+        assert_eq!(listing.items[0].offset, 4 * 32);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        // 2024-02-29 13:45:30 UTC
+        let now = 1_709_214_330;
+        let (date, time) = fat_time(now);
+        assert_eq!(date, 44 << 9 | 2 << 5 | 29);
+        assert_eq!(time, 13 << 11 | 45 << 5 | 15);
+        assert_eq!(unix_time(date, time), now);
+
+        assert_eq!(unix_time(0, 0), 0);
+        assert_eq!(fat_time(0), (1 << 5 | 1, 0));
+        assert_eq!(unix_time(fat_time(1).0, 0), 315_532_800);
+
+        let mut entry = ShortEntry::new(*b"FILE       ", 0, 0, 0, 0, 0);
+        entry.set_modified(now + 1);
+        assert_eq!(entry.modified(), now);
+        assert_ne!(entry.attributes() & attr::ARCHIVE, 0);
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Test Images
+//!
+//! Fresh volumes laid out the way `mkfs.vfat -F 32 -S 512 -s N` lays out
+//! an image file: 32 reserved sectors with the FSInfo in sector 1 and the
+//! backup boot sector in 6, two FATs sized with mkfs.fat's calculation,
+//! and an empty root directory in cluster 2.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::{BlockDevice, BlockInfo};
+use libsys::Result;
+
+use crate::Volume;
+
+/// Sectors of the default test image (8 MiB)
+pub const SECTORS: u32 = 16384;
+
+const RESERVED: u32 = 32;
+const FATS: u32 = 2;
+
+/// A disk in memory
+pub struct RamDisk(pub Vec<u8>);
+
+impl BlockDevice for RamDisk {
+    fn info(&self) -> BlockInfo {
+        BlockInfo {
+            block_size: 512,
+            max_transfer: 0,
+            block_count: (self.0.len() / 512) as u64,
+        }
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let start = lba as usize * 512;
+        buf.copy_from_slice(&self.0[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        let start = lba as usize * 512;
+        self.0[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// FAT size and cluster count, computed as mkfs.fat does
+fn layout(sectors: u32, sectors_per_cluster: u32) -> (u32, u32) {
+    let data = (sectors - RESERVED) as u64;
+    let clusters = (data * 512 + FATS as u64 * 8) / (sectors_per_cluster as u64 * 512 + FATS as u64 * 8);
+    let fat_sectors = ((clusters + 2) * 4).div_ceil(512) as u32;
+    let clusters = (sectors - RESERVED - FATS * fat_sectors) / sectors_per_cluster;
+    (fat_sectors, clusters.min(fat_sectors * 128 - 2))
+}
+
+pub fn boot_sector(sectors: u32, sectors_per_cluster: u32) -> [u8; 512] {
+    let (fat_sectors, _) = layout(sectors, sectors_per_cluster);
+    let mut sector = [0u8; 512];
+    sector[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    sector[3..11].copy_from_slice(b"mkfs.fat");
+    sector[11..13].copy_from_slice(&512u16.to_le_bytes());
+    sector[13] = sectors_per_cluster as u8;
+    sector[14..16].copy_from_slice(&(RESERVED as u16).to_le_bytes());
+    sector[16] = FATS as u8;
+    sector[21] = 0xF8;
+    sector[24..26].copy_from_slice(&32u16.to_le_bytes());
+    sector[26..28].copy_from_slice(&64u16.to_le_bytes());
+    sector[32..36].copy_from_slice(&sectors.to_le_bytes());
+    sector[36..40].copy_from_slice(&fat_sectors.to_le_bytes());
+    sector[44..48].copy_from_slice(&2u32.to_le_bytes());
+    sector[48..50].copy_from_slice(&1u16.to_le_bytes());
+    sector[50..52].copy_from_slice(&6u16.to_le_bytes());
+    sector[64] = 0x80;
+    sector[66] = 0x29;
+    sector[67..71].copy_from_slice(&0x1234_ABCDu32.to_le_bytes());
+    sector[71..82].copy_from_slice(b"NO NAME    ");
+    sector[82..90].copy_from_slice(b"FAT32   ");
+    sector[510] = 0x55;
+    sector[511] = 0xAA;
+    sector
+}
+
+pub fn fsinfo_sector(free: u32, next: u32) -> [u8; 512] {
+    let mut sector = [0u8; 512];
+    sector[0..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+    sector[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+    sector[488..492].copy_from_slice(&free.to_le_bytes());
+    sector[492..496].copy_from_slice(&next.to_le_bytes());
+    sector[508..512].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+    sector
+}
+
+/// A freshly formatted disk
+pub fn mkfs(sectors: u32, sectors_per_cluster: u32) -> RamDisk {
+    let (fat_sectors, clusters) = layout(sectors, sectors_per_cluster);
+    let mut disk = RamDisk(vec![0u8; sectors as usize * 512]);
+
+    let boot = boot_sector(sectors, sectors_per_cluster);
+    let fsinfo = fsinfo_sector(clusters - 1, 2);
+    for base in [0, 6] {
+        disk.write_blocks(base, &boot).unwrap();
+        disk.write_blocks(base + 1, &fsinfo).unwrap();
+    }
+
+    // Media byte, end of chain for the reserved entry, the root directory
+    let mut fat = [0u8; 12];
+    fat[0..4].copy_from_slice(&0x0FFF_FFF8u32.to_le_bytes());
+    fat[4..8].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+    fat[8..12].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+    for copy in 0..FATS {
+        let start = (RESERVED + copy * fat_sectors) as usize * 512;
+        disk.0[start..start + 12].copy_from_slice(&fat);
+    }
+    disk
+}
+
+/// Mount a freshly formatted 8 MiB volume
+pub fn mount(sectors_per_cluster: u32) -> Volume<RamDisk> {
+    Volume::mount(mkfs(SECTORS, sectors_per_cluster)).unwrap()
+}
@@ -0,0 +1,46 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! FAT32 Filesystem
+//!
+//! Reads and writes FAT32 volumes on any DDK `BlockDevice`, such as the
+//! EFI system partition of an NVMe or AHCI disk:
+//! - Long file names, and the 8.3 aliases other systems expect
+//! - Files up to 4 GiB, directories up to 65536 entries
+//! - Mirrored FATs and the FSInfo free cluster hints
+//! - `check`, a light fsck that reports what's wrong with a volume
+//!
+//! `FatServer` serves a volume over the Vfs protocol from libvfs.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::BlockDevice;
+//!
+//! fn serve<D: BlockDevice>(disk: D, channel: &ipc::Channel) -> libsys::Result<()> {
+//!     let volume = fat::Volume::mount(disk)?;
+//!     fat::FatServer::new(volume).run(channel)
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod bpb;
+pub mod check;
+pub mod dir;
+pub mod server;
+pub mod table;
+pub mod volume;
+
+#[cfg(test)]
+mod image;
+
+// Re-export commonly used types
+pub use check::{check, Problem, Report};
+pub use server::FatServer;
+pub use volume::{Node, Volume};
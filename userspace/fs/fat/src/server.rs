@@ -0,0 +1,441 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Vfs Server
+//!
+//! Serves a mounted volume over the Vfs protocol. Every open node keeps
+//! a copy of its short entry; after a write the other copies of the same
+//! node are refreshed, and a node that is open can't be removed or
+//! renamed. The FAT is written back whenever the client goes quiet.
+
+use alloc::vec::Vec;
+
+use ipc::Channel;
+use libddk::BlockDevice;
+use libsys::{clock, Error, Result, Status};
+use vfs::path;
+use vfs::{node_kind, open_flags, DirEntry, FsInfo, NodeAttr, Vfs, VfsServer, MAX_DIR_BATCH, MAX_TRANSFER};
+
+use crate::volume::Node;
+use crate::Volume;
+
+/// How long to sleep when no request is waiting (2ms)
+const POLL_INTERVAL: u64 = 2_000_000;
+
+struct Open {
+    node: Node,
+    flags: u32,
+}
+
+/// A filesystem server for one volume
+pub struct FatServer<D: BlockDevice> {
+    volume: Volume<D>,
+
+    /// Open nodes, indexed by node ID
+    nodes: Vec<Option<Open>>,
+}
+
+impl<D: BlockDevice> FatServer<D> {
+    pub fn new(volume: Volume<D>) -> Self {
+        Self {
+            volume,
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn volume(&mut self) -> &mut Volume<D> {
+        &mut self.volume
+    }
+
+    /// Serve requests from `channel` until the client closes it, then
+    /// sync the volume
+    pub fn run(self, channel: &Channel) -> Result<()> {
+        let mut server = VfsServer(self);
+        let mut unsynced = false;
+        loop {
+            match vfs::protocol::poll(&mut server, channel) {
+                Ok(true) => unsynced = true,
+                Ok(false) if unsynced => {
+                    server.0.volume.sync()?;
+                    unsynced = false;
+                }
+                Ok(false) => rt::timer::sleep(POLL_INTERVAL),
+                Err(e) if e.status() == Status::PeerClosed => break,
+                Err(e) => return Err(e),
+            }
+        }
+        server.0.volume.sync()
+    }
+
+    /// Stamp the changes about to be made with the current time
+    fn touch(&mut self) {
+        if let Ok(now) = clock::utc() {
+            self.volume.set_time(now.max(0) as u64 / 1_000_000_000);
+        }
+    }
+
+    fn open_node(&mut self, id: u32) -> Result<&mut Open> {
+        self.nodes
+            .get_mut(id as usize)
+            .and_then(Option::as_mut)
+            .ok_or(Error::new(Status::InvalidArgs))
+    }
+
+    /// Node `id`, if it was opened with all of `flags`
+    fn node_for(&mut self, id: u32, flags: u32) -> Result<Node> {
+        let open = self.open_node(id)?;
+        if open.flags & flags != flags {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        Ok(open.node)
+    }
+
+    /// Store a changed node in every open copy of it
+    fn refresh(&mut self, node: Node) {
+        for open in self.nodes.iter_mut().flatten() {
+            if open.node.location == node.location {
+                open.node = node;
+            }
+        }
+    }
+
+    /// Fail with `Busy` if `node` is open
+    fn check_closed(&self, node: &Node) -> Result<()> {
+        match self
+            .nodes
+            .iter()
+            .flatten()
+            .any(|open| open.node.location == node.location)
+        {
+            true => Err(Error::new(Status::Busy)),
+            false => Ok(()),
+        }
+    }
+
+    /// The directory at `parts`
+    fn directory(&mut self, parts: &[&str]) -> Result<Node> {
+        let dir = self.volume.resolve(parts)?;
+        if !dir.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        Ok(dir)
+    }
+
+    fn lookup_or_create(&mut self, path: &[u8], flags: u32) -> Result<Node> {
+        let parts = path::components(path)?;
+        let Some((&name, parents)) = parts.split_last() else {
+            return Ok(self.volume.root());
+        };
+        let dir = self.directory(parents)?;
+        match self.volume.lookup(&dir, name) {
+            Ok(_)
+                if flags & (open_flags::CREATE | open_flags::EXCLUSIVE)
+                    == open_flags::CREATE | open_flags::EXCLUSIVE =>
+            {
+                Err(Error::new(Status::AlreadyExists))
+            }
+            Err(e) if e.status() == Status::NotFound && flags & open_flags::CREATE != 0 => {
+                if flags & open_flags::DIRECTORY != 0 {
+                    return Err(Error::new(Status::InvalidArgs));
+                }
+                self.touch();
+                self.volume.create(&dir, name, false)
+            }
+            result => result,
+        }
+    }
+}
+
+impl<D: BlockDevice> Vfs for FatServer<D> {
+    fn query(&mut self) -> Result<FsInfo> {
+        Ok(FsInfo {
+            name: b"fat32".to_vec(),
+            label: self.volume.label()?.into_bytes(),
+            block_size: self.volume.cluster_size(),
+            total_bytes: self.volume.total_bytes(),
+            free_bytes: self.volume.free_bytes(),
+        })
+    }
+
+    fn open(&mut self, path: Vec<u8>, flags: u32) -> Result<u32> {
+        let mut node = self.lookup_or_create(&path, flags)?;
+        if node.is_dir() && flags & (open_flags::WRITE | open_flags::TRUNCATE) != 0 {
+            return Err(Error::new(Status::WrongType));
+        }
+        if !node.is_dir() && flags & open_flags::DIRECTORY != 0 {
+            return Err(Error::new(Status::WrongType));
+        }
+        if node.read_only() && flags & open_flags::WRITE != 0 {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        if flags & open_flags::TRUNCATE != 0 {
+            if flags & open_flags::WRITE == 0 {
+                return Err(Error::new(Status::InvalidArgs));
+            }
+            if node.size() > 0 {
+                self.touch();
+                self.volume.truncate(&mut node, 0)?;
+                self.refresh(node);
+            }
+        }
+
+        let open = Some(Open { node, flags });
+        match self.nodes.iter().position(Option::is_none) {
+            Some(id) => {
+                self.nodes[id] = open;
+                Ok(id as u32)
+            }
+            None => {
+                self.nodes.push(open);
+                Ok(self.nodes.len() as u32 - 1)
+            }
+        }
+    }
+
+    fn close(&mut self, node: u32) -> Result<()> {
+        self.open_node(node)?;
+        self.nodes[node as usize] = None;
+        Ok(())
+    }
+
+    fn stat(&mut self, node: u32) -> Result<NodeAttr> {
+        let node = self.open_node(node)?.node;
+        Ok(NodeAttr {
+            kind: if node.is_dir() {
+                node_kind::DIRECTORY
+            } else {
+                node_kind::FILE
+            },
+            size: node.size() as u64,
+            // The root has no entry to hold a time
+            modified: if node.location.is_some() {
+                node.entry.modified()
+            } else {
+                0
+            },
+            read_only: node.read_only(),
+        })
+    }
+
+    fn read(&mut self, node: u32, offset: u64, len: u32) -> Result<Vec<u8>> {
+        if len > MAX_TRANSFER {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let node = self.node_for(node, open_flags::READ)?;
+        let mut data = alloc::vec![0u8; len as usize];
+        let read = self.volume.read(&node, offset, &mut data)?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    fn write(&mut self, node: u32, offset: u64, data: Vec<u8>) -> Result<u32> {
+        if data.len() > MAX_TRANSFER as usize {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let mut node = self.node_for(node, open_flags::WRITE)?;
+        self.touch();
+        let written = self.volume.write(&mut node, offset, &data);
+        // A failed write may still have grown the file
+        if let Some(location) = node.location {
+            node = self.volume.lookup_at(location)?;
+        }
+        self.refresh(node);
+        Ok(written? as u32)
+    }
+
+    fn truncate(&mut self, node: u32, size: u64) -> Result<()> {
+        let mut node = self.node_for(node, open_flags::WRITE)?;
+        self.touch();
+        self.volume.truncate(&mut node, size)?;
+        self.refresh(node);
+        Ok(())
+    }
+
+    fn read_dir(&mut self, node: u32, cookie: u64) -> Result<Vec<DirEntry>> {
+        let node = self.open_node(node)?.node;
+        let listing = self.volume.list(&node)?;
+        Ok(listing
+            .items
+            .iter()
+            .filter(|item| item.offset as u64 >= cookie)
+            .take(MAX_DIR_BATCH as usize)
+            .map(|item| DirEntry {
+                name: item.name.clone().into_bytes(),
+                kind: if item.entry.is_dir() {
+                    node_kind::DIRECTORY
+                } else {
+                    node_kind::FILE
+                },
+                size: if item.entry.is_dir() {
+                    0
+                } else {
+                    item.entry.size() as u64
+                },
+                cookie: item.offset as u64 + 32,
+            })
+            .collect())
+    }
+
+    fn mkdir(&mut self, path: Vec<u8>) -> Result<()> {
+        let (parents, name) = path::split_last(&path)?;
+        let dir = self.directory(&parents)?;
+        self.touch();
+        self.volume.create(&dir, name, true)?;
+        Ok(())
+    }
+
+    fn unlink(&mut self, path: Vec<u8>) -> Result<()> {
+        let (parents, name) = path::split_last(&path)?;
+        let dir = self.directory(&parents)?;
+        let node = self.volume.lookup(&dir, name)?;
+        self.check_closed(&node)?;
+        self.volume.remove(&dir, name)
+    }
+
+    fn rename(&mut self, from: Vec<u8>, to: Vec<u8>) -> Result<()> {
+        let (from_parents, from_name) = path::split_last(&from)?;
+        let (to_parents, to_name) = path::split_last(&to)?;
+        let from_dir = self.directory(&from_parents)?;
+        let to_dir = self.directory(&to_parents)?;
+        let node = self.volume.lookup(&from_dir, from_name)?;
+        self.check_closed(&node)?;
+        self.touch();
+        self.volume.rename(&from_dir, from_name, &to_dir, to_name)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.volume.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{self, RamDisk};
+    use open_flags::{CREATE, DIRECTORY, EXCLUSIVE, READ, TRUNCATE, WRITE};
+
+    fn server() -> FatServer<RamDisk> {
+        FatServer::new(image::mount(1))
+    }
+
+    fn names(server: &mut FatServer<RamDisk>, dir: u32) -> Vec<Vec<u8>> {
+        let mut names = Vec::new();
+        let mut cookie = 0;
+        loop {
+            let batch = server.read_dir(dir, cookie).unwrap();
+            let Some(last) = batch.last() else {
+                return names;
+            };
+            cookie = last.cookie;
+            names.extend(batch.into_iter().map(|entry| entry.name));
+        }
+    }
+
+    #[test]
+    fn test_files() {
+        let mut server = server();
+        let info = server.query().unwrap();
+        assert_eq!(info.name, b"fat32");
+        assert!(info.label.is_empty());
+        assert_eq!(info.block_size, 512);
+
+        server.mkdir(b"/EFI".to_vec()).unwrap();
+        server.mkdir(b"/EFI/BOOT".to_vec()).unwrap();
+        let file = server
+            .open(b"/EFI/BOOT/BOOTX64.EFI".to_vec(), READ | WRITE | CREATE)
+            .unwrap();
+        assert_eq!(server.write(file, 0, alloc::vec![0x4D; 3000]).unwrap(), 3000);
+        assert_eq!(server.read(file, 2990, 100).unwrap(), [0x4D; 10]);
+        assert!(server.read(file, 0, MAX_TRANSFER + 1).is_err());
+
+        // A second open sees the first one's writes
+        let other = server.open(b"EFI/./BOOT/../BOOT/bootx64.efi".to_vec(), READ).unwrap();
+        server.write(file, 3000, b"tail".to_vec()).unwrap();
+        assert_eq!(server.stat(other).unwrap().size, 3004);
+        assert_eq!(server.read(other, 3000, 10).unwrap(), b"tail");
+        assert_eq!(
+            server.write(other, 0, b"x".to_vec()).unwrap_err().status(),
+            Status::AccessDenied
+        );
+
+        server.truncate(file, 10).unwrap();
+        assert_eq!(server.stat(other).unwrap().size, 10);
+        server.close(file).unwrap();
+        server.close(other).unwrap();
+        assert_eq!(server.close(other).unwrap_err().status(), Status::InvalidArgs);
+
+        let file = server
+            .open(b"/EFI/BOOT/BOOTX64.EFI".to_vec(), WRITE | TRUNCATE)
+            .unwrap();
+        assert_eq!(server.stat(file).unwrap().size, 0);
+        assert_eq!(
+            server
+                .open(b"/EFI/BOOT/BOOTX64.EFI".to_vec(), CREATE | EXCLUSIVE)
+                .unwrap_err()
+                .status(),
+            Status::AlreadyExists
+        );
+        assert_eq!(
+            server.open(b"/EFI".to_vec(), WRITE).unwrap_err().status(),
+            Status::WrongType
+        );
+        assert_eq!(
+            server
+                .open(b"/EFI/BOOT/BOOTX64.EFI".to_vec(), DIRECTORY)
+                .unwrap_err()
+                .status(),
+            Status::WrongType
+        );
+        assert_eq!(
+            server.open(b"/missing/file".to_vec(), CREATE).unwrap_err().status(),
+            Status::NotFound
+        );
+    }
+
+    #[test]
+    fn test_directories() {
+        let mut server = server();
+        server.mkdir(b"/docs".to_vec()).unwrap();
+        for index in 0..100 {
+            let path = alloc::format!("/docs/Chapter {}.txt", index);
+            let file = server.open(path.into_bytes(), CREATE | WRITE).unwrap();
+            server.close(file).unwrap();
+        }
+        let docs = server.open(b"/docs".to_vec(), READ | DIRECTORY).unwrap();
+        let listed = names(&mut server, docs);
+        assert_eq!(listed.len(), 100);
+        assert_eq!(listed[42], b"Chapter 42.txt");
+
+        // Open nodes can't be removed or renamed, and full directories
+        // can't be removed
+        let file = server.open(b"/docs/Chapter 1.txt".to_vec(), READ).unwrap();
+        assert_eq!(
+            server.unlink(b"/docs/Chapter 1.txt".to_vec()).unwrap_err().status(),
+            Status::Busy
+        );
+        assert_eq!(
+            server
+                .rename(b"/docs/Chapter 1.txt".to_vec(), b"/one".to_vec())
+                .unwrap_err()
+                .status(),
+            Status::Busy
+        );
+        assert_eq!(server.unlink(b"/docs".to_vec()).unwrap_err().status(), Status::Busy);
+        server.close(docs).unwrap();
+        assert_eq!(server.unlink(b"/docs".to_vec()).unwrap_err().status(), Status::BadState);
+        server.close(file).unwrap();
+
+        server
+            .rename(b"/docs/Chapter 1.txt".to_vec(), b"/one".to_vec())
+            .unwrap();
+        server.unlink(b"/docs/Chapter 2.txt".to_vec()).unwrap();
+        let root = server.open(b"/".to_vec(), READ).unwrap();
+        assert_eq!(names(&mut server, root), [b"docs".to_vec(), b"one".to_vec()]);
+        assert_eq!(server.stat(root).unwrap().kind, node_kind::DIRECTORY);
+        server.sync().unwrap();
+        assert!(crate::check(server.volume()).unwrap().is_clean());
+    }
+}
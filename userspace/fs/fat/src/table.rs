@@ -0,0 +1,334 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! File Allocation Table
+//!
+//! Each data cluster has a 32-bit entry, of which the low 28 bits count:
+//! 0 for a free cluster, the next cluster of a chain, or an end-of-chain
+//! marker. The whole table is kept in memory, which costs 4 bytes a
+//! cluster (512 KiB for a 512 MiB system partition with 4 KiB
+//! clusters); changed sectors are written back to every FAT copy by
+//! `flush`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::bpb::{BootSector, FIRST_CLUSTER};
+
+/// Entry of a free cluster
+pub const FREE: u32 = 0;
+
+/// Entry of a cluster marked bad
+pub const BAD: u32 = 0x0FFF_FFF7;
+
+/// Entry written to end a chain; anything from `END_MIN` up also ends one
+pub const END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+pub const END_MIN: u32 = 0x0FFF_FFF8;
+
+/// Bits of an entry that count
+const ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+/// Largest read when loading the table
+const LOAD_CHUNK: usize = 32 * 1024;
+
+/// Whether `entry` ends a chain
+pub fn is_end(entry: u32) -> bool {
+    entry >= END_MIN
+}
+
+/// The table of a mounted volume
+pub struct Fat {
+    /// Raw entries, high bits included
+    entries: Vec<u32>,
+
+    /// Sectors changed since the last flush
+    dirty: Vec<bool>,
+
+    entries_per_sector: usize,
+    free: u32,
+
+    /// Where the next allocation starts looking
+    next_free: u32,
+}
+
+impl Fat {
+    /// Table of `clusters` data clusters with all entries free
+    pub fn new(clusters: u32, sector_size: u32) -> Self {
+        let entries_per_sector = sector_size as usize / 4;
+        let len = clusters as usize + FIRST_CLUSTER as usize;
+        Self {
+            entries: vec![FREE; len],
+            dirty: vec![false; len.div_ceil(entries_per_sector)],
+            entries_per_sector,
+            free: clusters,
+            next_free: FIRST_CLUSTER,
+        }
+    }
+
+    /// Read the table of the volume `bpb` describes
+    ///
+    /// Reads the active FAT, or the first if they are mirrored.
+    pub fn load<D: BlockDevice>(device: &mut D, bpb: &BootSector) -> Result<Self> {
+        let mut fat = Self::new(bpb.cluster_count(), bpb.bytes_per_sector);
+        let bytes = read_copy(device, bpb, bpb.active_fat.unwrap_or(0), fat.entries.len())?;
+        for (entry, raw) in fat.entries.iter_mut().zip(bytes.chunks_exact(4)) {
+            *entry = u32::from_le_bytes(raw.try_into().unwrap());
+        }
+        fat.free = fat.entries[FIRST_CLUSTER as usize..]
+            .iter()
+            .filter(|&&e| e & ENTRY_MASK == FREE)
+            .count() as u32;
+        Ok(fat)
+    }
+
+    /// Number of entries, the two reserved ones included
+    pub fn len(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    /// Whether the table has no data clusters
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() <= FIRST_CLUSTER as usize
+    }
+
+    /// Whether `cluster` is a data cluster of the volume
+    pub fn contains(&self, cluster: u32) -> bool {
+        (FIRST_CLUSTER..self.len()).contains(&cluster)
+    }
+
+    /// Entry of `cluster`
+    pub fn get(&self, cluster: u32) -> u32 {
+        self.entries[cluster as usize] & ENTRY_MASK
+    }
+
+    /// Set the entry of `cluster`, keeping its reserved high bits
+    pub fn set(&mut self, cluster: u32, value: u32) {
+        let entry = &mut self.entries[cluster as usize];
+        let was_free = *entry & ENTRY_MASK == FREE;
+        *entry = (*entry & !ENTRY_MASK) | (value & ENTRY_MASK);
+        match (was_free, value == FREE) {
+            (true, false) => self.free -= 1,
+            (false, true) => self.free += 1,
+            _ => {}
+        }
+        self.dirty[cluster as usize / self.entries_per_sector] = true;
+    }
+
+    /// Free data clusters
+    pub fn free_count(&self) -> u32 {
+        self.free
+    }
+
+    /// Where the next allocation starts looking
+    pub fn next_free(&self) -> u32 {
+        self.next_free
+    }
+
+    /// Start the next allocation at `cluster`, if it's a data cluster
+    pub fn set_next_free(&mut self, cluster: u32) {
+        if self.contains(cluster) {
+            self.next_free = cluster;
+        }
+    }
+
+    /// Cluster after `cluster` in its chain, or `None` at the end
+    ///
+    /// Fails with `IoError` if the entry is free, bad or out of range.
+    pub fn next(&self, cluster: u32) -> Result<Option<u32>> {
+        match self.get(cluster) {
+            entry if is_end(entry) => Ok(None),
+            entry if self.contains(entry) => Ok(Some(entry)),
+            _ => Err(Error::new(Status::IoError)),
+        }
+    }
+
+    /// The clusters of the chain starting at `first`; none for cluster 0
+    ///
+    /// Fails with `IoError` if the chain is broken or loops.
+    pub fn chain(&self, first: u32) -> Result<Vec<u32>> {
+        let mut clusters = Vec::new();
+        if first == 0 {
+            return Ok(clusters);
+        }
+        if !self.contains(first) {
+            return Err(Error::new(Status::IoError));
+        }
+        let mut cluster = Some(first);
+        while let Some(current) = cluster {
+            // A chain longer than the volume has clusters loops
+            if clusters.len() >= self.entries.len() {
+                return Err(Error::new(Status::IoError));
+            }
+            clusters.push(current);
+            cluster = self.next(current)?;
+        }
+        Ok(clusters)
+    }
+
+    /// Chain `count` free clusters, returning them
+    ///
+    /// The new chain is appended to `tail` if given. Fails with `NoMemory`
+    /// if the volume doesn't have that many free clusters.
+    pub fn allocate(&mut self, count: u32, tail: Option<u32>) -> Result<Vec<u32>> {
+        if count > self.free {
+            return Err(Error::new(Status::NoMemory));
+        }
+
+        let mut clusters = Vec::with_capacity(count as usize);
+        let mut cluster = self.next_free;
+        while clusters.len() < count as usize {
+            if !self.contains(cluster) {
+                cluster = FIRST_CLUSTER;
+            }
+            if self.get(cluster) == FREE {
+                clusters.push(cluster);
+            }
+            cluster += 1;
+        }
+        self.next_free = cluster;
+
+        let mut previous = tail;
+        for &cluster in &clusters {
+            if let Some(previous) = previous {
+                self.set(previous, cluster);
+            }
+            previous = Some(cluster);
+        }
+        if let Some(&last) = clusters.last() {
+            self.set(last, END_OF_CHAIN);
+        }
+        Ok(clusters)
+    }
+
+    /// Free the chain starting at `first`
+    pub fn free_chain(&mut self, first: u32) -> Result<()> {
+        for cluster in self.chain(first)? {
+            self.set(cluster, FREE);
+        }
+        Ok(())
+    }
+
+    /// Write the changed sectors to every FAT in use
+    pub fn flush<D: BlockDevice>(&mut self, device: &mut D, bpb: &BootSector) -> Result<()> {
+        let sector_size = self.entries_per_sector * 4;
+        let mut sector = vec![0u8; sector_size];
+        for index in 0..self.dirty.len() {
+            if !self.dirty[index] {
+                continue;
+            }
+
+            sector.fill(0);
+            let first = index * self.entries_per_sector;
+            let entries = &self.entries[first..self.entries.len().min(first + self.entries_per_sector)];
+            for (raw, entry) in sector.chunks_exact_mut(4).zip(entries) {
+                raw.copy_from_slice(&entry.to_le_bytes());
+            }
+
+            // Sectors past the last cluster's entry keep their zeros
+            let copies = match bpb.active_fat {
+                Some(active) => active..active + 1,
+                None => 0..bpb.fat_count,
+            };
+            for copy in copies {
+                device.write_blocks((bpb.fat_start(copy) as usize + index) as u64, &sector)?;
+            }
+            self.dirty[index] = false;
+        }
+        Ok(())
+    }
+
+    /// Whether any entry changed since the last flush
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.iter().any(|&dirty| dirty)
+    }
+}
+
+/// Read the first `entries` entries of FAT `copy` as bytes
+pub fn read_copy<D: BlockDevice>(device: &mut D, bpb: &BootSector, copy: u32, entries: usize) -> Result<Vec<u8>> {
+    let sector_size = bpb.bytes_per_sector as usize;
+    let sectors = (entries * 4).div_ceil(sector_size);
+    let mut bytes = vec![0u8; sectors * sector_size];
+    let start = bpb.fat_start(copy) as u64;
+    for (index, chunk) in bytes.chunks_mut(LOAD_CHUNK).enumerate() {
+        let lba = start + (index * LOAD_CHUNK / sector_size) as u64;
+        device.read_blocks(lba, chunk)?;
+    }
+    bytes.truncate(entries * 4);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image;
+
+    #[test]
+    fn test_allocate_and_free() {
+        let mut fat = Fat::new(16, 512);
+        assert_eq!(fat.free_count(), 16);
+
+        let first = fat.allocate(3, None).unwrap();
+        assert_eq!(first, [2, 3, 4]);
+        let more = fat.allocate(2, Some(4)).unwrap();
+        assert_eq!(more, [5, 6]);
+        assert_eq!(fat.chain(2).unwrap(), [2, 3, 4, 5, 6]);
+        assert_eq!(fat.free_count(), 11);
+
+        // Freed clusters are found again once the search wraps around
+        fat.free_chain(2).unwrap();
+        assert_eq!(fat.free_count(), 16);
+        assert_eq!(fat.allocate(14, None).unwrap()[11..], [2, 3, 4]);
+        assert_eq!(fat.allocate(2, None).unwrap(), [5, 6]);
+        assert_eq!(fat.allocate(1, None).unwrap_err().status(), Status::NoMemory);
+        assert!(fat.chain(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_broken_chains() {
+        let mut fat = Fat::new(16, 512);
+        fat.set(2, 3);
+        fat.set(3, 2);
+        assert_eq!(fat.chain(2).unwrap_err().status(), Status::IoError);
+
+        // Into a free cluster, and past the end of the volume
+        fat.set(3, 4);
+        assert!(fat.chain(2).is_err());
+        fat.set(3, 100);
+        assert!(fat.chain(2).is_err());
+        fat.set(3, BAD);
+        assert!(fat.chain(2).is_err());
+    }
+
+    #[test]
+    fn test_load_and_flush() {
+        let mut disk = image::mkfs(image::SECTORS, 1);
+        let bpb = BootSector::parse(&image::boot_sector(image::SECTORS, 1)).unwrap();
+        let mut fat = Fat::load(&mut disk, &bpb).unwrap();
+        assert_eq!(fat.get(0), 0x0FFF_FFF8);
+        assert!(fat.next(2).unwrap().is_none());
+        assert_eq!(fat.free_count(), bpb.cluster_count() - 1);
+        assert!(!fat.is_dirty());
+
+        fat.set_next_free(3);
+        let chain = fat.allocate(200, Some(2)).unwrap();
+        assert!(fat.is_dirty());
+        fat.flush(&mut disk, &bpb).unwrap();
+        assert!(!fat.is_dirty());
+
+        // Both copies were written
+        let entries = fat.len() as usize;
+        assert_eq!(
+            read_copy(&mut disk, &bpb, 0, entries).unwrap(),
+            read_copy(&mut disk, &bpb, 1, entries).unwrap()
+        );
+        let loaded = Fat::load(&mut disk, &bpb).unwrap();
+        assert_eq!(loaded.chain(2).unwrap().len(), 1 + chain.len());
+        assert_eq!(loaded.free_count(), fat.free_count());
+    }
+}
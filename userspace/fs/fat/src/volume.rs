@@ -0,0 +1,871 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Mounted Volumes
+//!
+//! A `Volume` works on a block device holding one FAT32 filesystem, such
+//! as a partition instance of a disk. File data and directory entries go
+//! to the device as soon as they change; the FAT and the FSInfo hints
+//! are written by `sync`, so a volume that isn't synced loses the
+//! clusters it allocated since the last one.
+//!
+//! Files and directories are `Node`s: a copy of the node's short entry
+//! and where that entry lives. Changing a file updates the copy and
+//! writes it back, so a caller holding two copies of one node must
+//! refresh the other.
+//!
+//! The volume has no clock; changes are stamped with the time last given
+//! to `set_time`.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::bpb::{BootSector, FsInfo};
+use crate::dir::{self, attr, Item, Listing, ShortEntry, DELETED, DOT, DOTDOT, END, ENTRY_SIZE};
+use crate::table::{Fat, END_OF_CHAIN, FREE};
+
+/// Largest directory, in bytes (65536 entries)
+const MAX_DIR_SIZE: usize = 65536 * ENTRY_SIZE;
+
+/// Where a node's short entry is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// First cluster of the directory holding the entry
+    pub dir: u32,
+
+    /// Byte offset of the entry in that directory
+    pub offset: u32,
+}
+
+/// A file or directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    pub entry: ShortEntry,
+
+    /// `None` for the root directory, which has no entry
+    pub location: Option<Location>,
+}
+
+impl Node {
+    pub fn is_dir(&self) -> bool {
+        self.entry.is_dir()
+    }
+
+    /// First cluster, 0 for an empty file
+    pub fn cluster(&self) -> u32 {
+        self.entry.cluster()
+    }
+
+    /// Size in bytes; 0 for directories
+    pub fn size(&self) -> u32 {
+        if self.is_dir() {
+            0
+        } else {
+            self.entry.size()
+        }
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.entry.attributes() & attr::READ_ONLY != 0
+    }
+}
+
+/// The item named `name`, preferring a long name match over an 8.3 one
+fn find<'a>(items: &'a [Item], name: &str) -> Option<&'a Item> {
+    items
+        .iter()
+        .find(|item| dir::same_name(&item.name, name))
+        .or_else(|| items.iter().find(|item| item.matches(name)))
+}
+
+/// A mounted FAT32 volume
+pub struct Volume<D: BlockDevice> {
+    device: D,
+    bpb: BootSector,
+    fat: Fat,
+    cluster_size: usize,
+
+    /// Time stamped on changes, in seconds since the Unix epoch
+    now: u64,
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// Mount the volume on `device`
+    ///
+    /// Fails with `NotSupported` if the device doesn't hold a FAT32
+    /// volume with its block size.
+    pub fn mount(mut device: D) -> Result<Self> {
+        let info = device.info();
+        let mut sector = vec![0u8; info.block_size as usize];
+        device.read_blocks(0, &mut sector)?;
+        let bpb = BootSector::parse(&sector)?;
+        if bpb.bytes_per_sector != info.block_size || bpb.total_sectors as u64 > info.block_count {
+            return Err(Error::new(Status::NotSupported));
+        }
+
+        let mut fat = Fat::load(&mut device, &bpb)?;
+        if let Some(sector_index) = bpb.fsinfo_sector {
+            device.read_blocks(sector_index as u64, &mut sector)?;
+            if let Some(next) = FsInfo::parse(&sector).and_then(|fsinfo| fsinfo.next_free) {
+                fat.set_next_free(next);
+            }
+        }
+
+        Ok(Self {
+            device,
+            cluster_size: bpb.cluster_size() as usize,
+            bpb,
+            fat,
+            now: 0,
+        })
+    }
+
+    pub fn boot_sector(&self) -> &BootSector {
+        &self.bpb
+    }
+
+    pub fn fat(&self) -> &Fat {
+        &self.fat
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Give the device back; unsynced changes are lost
+    pub fn into_device(self) -> D {
+        self.device
+    }
+
+    /// Bytes in a cluster
+    pub fn cluster_size(&self) -> u32 {
+        self.cluster_size as u32
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.bpb.cluster_count() as u64 * self.cluster_size as u64
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.fat.free_count() as u64 * self.cluster_size as u64
+    }
+
+    /// Set the time changes are stamped with, in seconds since the Unix
+    /// epoch
+    pub fn set_time(&mut self, now: u64) {
+        self.now = now;
+    }
+
+    /// The volume label; empty if there is none
+    pub fn label(&mut self) -> Result<String> {
+        let root = self.root();
+        let label = self.list(&root)?.label.unwrap_or(self.bpb.label);
+        // OEM code page bytes are taken as Latin-1
+        let label: String = label.iter().map(|&b| b as char).collect();
+        let label = label.trim_end();
+        Ok(if label == "NO NAME" {
+            String::new()
+        } else {
+            String::from(label)
+        })
+    }
+
+    /// The root directory
+    pub fn root(&self) -> Node {
+        let mut entry = ShortEntry([0u8; ENTRY_SIZE]);
+        entry.0[11] = attr::DIRECTORY;
+        entry.set_cluster(self.bpb.root_cluster);
+        Node { entry, location: None }
+    }
+
+    fn cluster_lba(&self, cluster: u32) -> u64 {
+        self.bpb.cluster_sector(cluster) as u64
+    }
+
+    fn read_cluster(&mut self, cluster: u32, buf: &mut [u8]) -> Result<()> {
+        let lba = self.cluster_lba(cluster);
+        self.device.read_blocks(lba, buf)
+    }
+
+    fn write_cluster(&mut self, cluster: u32, buf: &[u8]) -> Result<()> {
+        let lba = self.cluster_lba(cluster);
+        self.device.write_blocks(lba, buf)
+    }
+
+    /// Data of the clusters `clusters`, one after the other
+    pub fn read_clusters(&mut self, clusters: &[u32]) -> Result<Vec<u8>> {
+        let mut data = vec![0u8; clusters.len() * self.cluster_size];
+        for (&cluster, chunk) in clusters.iter().zip(data.chunks_exact_mut(self.cluster_size)) {
+            self.read_cluster(cluster, chunk)?;
+        }
+        Ok(data)
+    }
+
+    /// A directory's clusters and data
+    fn dir_data(&mut self, dir: &Node) -> Result<(Vec<u32>, Vec<u8>)> {
+        if !dir.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        let chain = self.fat.chain(dir.cluster())?;
+        let data = self.read_clusters(&chain)?;
+        Ok((chain, data))
+    }
+
+    /// Write back the clusters of directory data touched by bytes
+    /// `start..end`
+    fn write_dir(&mut self, chain: &[u32], data: &[u8], start: usize, end: usize) -> Result<()> {
+        let size = self.cluster_size;
+        for index in start / size..end.div_ceil(size) {
+            self.write_cluster(chain[index], &data[index * size..(index + 1) * size])?;
+        }
+        Ok(())
+    }
+
+    /// What directory `dir` holds
+    pub fn list(&mut self, dir: &Node) -> Result<Listing> {
+        let (_, data) = self.dir_data(dir)?;
+        Ok(dir::parse(&data))
+    }
+
+    /// The node called `name` in directory `dir`
+    pub fn lookup(&mut self, dir: &Node, name: &str) -> Result<Node> {
+        let listing = self.list(dir)?;
+        let item = find(&listing.items, name).ok_or(Error::new(Status::NotFound))?;
+        Ok(Node {
+            entry: item.entry,
+            location: Some(Location {
+                dir: dir.cluster(),
+                offset: item.offset,
+            }),
+        })
+    }
+
+    /// The node at the end of `path`, from the root
+    pub fn resolve(&mut self, path: &[&str]) -> Result<Node> {
+        let mut node = self.root();
+        for name in path {
+            node = self.lookup(&node, name)?;
+        }
+        Ok(node)
+    }
+
+    /// Read from a file at `offset`, returning the bytes read
+    pub fn read(&mut self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if node.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        let size = node.size() as u64;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+
+        let chain = self.fat.chain(node.cluster())?;
+        let mut scratch = vec![0u8; self.cluster_size];
+        let mut done = 0;
+        while done < len {
+            let pos = offset as usize + done;
+            let cluster = *chain.get(pos / self.cluster_size).ok_or(Error::new(Status::IoError))?;
+            let within = pos % self.cluster_size;
+            let count = (self.cluster_size - within).min(len - done);
+            if count == self.cluster_size {
+                self.read_cluster(cluster, &mut buf[done..done + count])?;
+            } else {
+                self.read_cluster(cluster, &mut scratch)?;
+                buf[done..done + count].copy_from_slice(&scratch[within..within + count]);
+            }
+            done += count;
+        }
+        Ok(len)
+    }
+
+    /// Fail unless `node` is a file that may be written
+    fn check_writable(node: &Node) -> Result<()> {
+        if node.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        if node.read_only() {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        Ok(())
+    }
+
+    /// Make a file's chain long enough for `size` bytes, returning it
+    fn grow(&mut self, node: &mut Node, size: u64) -> Result<Vec<u32>> {
+        let mut chain = self.fat.chain(node.cluster())?;
+        let needed = (size as usize).div_ceil(self.cluster_size);
+        if chain.len() < needed {
+            let added = self
+                .fat
+                .allocate((needed - chain.len()) as u32, chain.last().copied())?;
+            if chain.is_empty() {
+                node.entry.set_cluster(added[0]);
+            }
+            chain.extend(added);
+        }
+        Ok(chain)
+    }
+
+    /// Write `data` into the chain at bytes `offset..`, or zeros if `data`
+    /// is `None`
+    fn fill(&mut self, chain: &[u32], offset: u64, len: usize, data: Option<&[u8]>) -> Result<()> {
+        let mut scratch = vec![0u8; self.cluster_size];
+        let mut done = 0;
+        while done < len {
+            let pos = offset as usize + done;
+            let cluster = chain[pos / self.cluster_size];
+            let within = pos % self.cluster_size;
+            let count = (self.cluster_size - within).min(len - done);
+            if count < self.cluster_size {
+                self.read_cluster(cluster, &mut scratch)?;
+            }
+            match data {
+                Some(data) => scratch[within..within + count].copy_from_slice(&data[done..done + count]),
+                None => scratch[within..within + count].fill(0),
+            }
+            self.write_cluster(cluster, &scratch)?;
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Write a node's entry back to its directory
+    fn store(&mut self, node: &Node) -> Result<()> {
+        let Some(location) = node.location else {
+            return Ok(());
+        };
+        let chain = self.fat.chain(location.dir)?;
+        let offset = location.offset as usize;
+        let cluster = *chain
+            .get(offset / self.cluster_size)
+            .ok_or(Error::new(Status::IoError))?;
+        let mut data = vec![0u8; self.cluster_size];
+        self.read_cluster(cluster, &mut data)?;
+        let within = offset % self.cluster_size;
+        data[within..within + ENTRY_SIZE].copy_from_slice(&node.entry.0);
+        self.write_cluster(cluster, &data)
+    }
+
+    /// Write `data` to a file at `offset`, returning the bytes written
+    ///
+    /// A gap between the end of the file and `offset` is filled with
+    /// zeros. Fails with `InvalidArgs` past FAT32's 4 GiB file limit.
+    pub fn write(&mut self, node: &mut Node, offset: u64, data: &[u8]) -> Result<usize> {
+        Self::check_writable(node)?;
+        let end = offset
+            .checked_add(data.len() as u64)
+            .filter(|&end| end <= u32::MAX as u64)
+            .ok_or(Error::new(Status::InvalidArgs))?;
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if offset > node.size() as u64 {
+            self.truncate(node, offset)?;
+        }
+
+        let chain = self.grow(node, end)?;
+        self.fill(&chain, offset, data.len(), Some(data))?;
+        node.entry.set_size(node.size().max(end as u32));
+        node.entry.set_modified(self.now);
+        self.store(node)?;
+        Ok(data.len())
+    }
+
+    /// Cut or extend a file to `size` bytes, extending with zeros
+    pub fn truncate(&mut self, node: &mut Node, size: u64) -> Result<()> {
+        Self::check_writable(node)?;
+        if size > u32::MAX as u64 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let old = node.size() as u64;
+        if size < old {
+            let chain = self.fat.chain(node.cluster())?;
+            let keep = (size as usize).div_ceil(self.cluster_size);
+            if keep == 0 {
+                node.entry.set_cluster(0);
+            } else if let Some(&last) = chain.get(keep - 1) {
+                self.fat.set(last, END_OF_CHAIN);
+            }
+            for &cluster in chain.iter().skip(keep) {
+                self.fat.set(cluster, FREE);
+            }
+        } else if size > old {
+            let chain = self.grow(node, size)?;
+            self.fill(&chain, old, (size - old) as usize, None)?;
+        }
+
+        node.entry.set_size(size as u32);
+        node.entry.set_modified(self.now);
+        self.store(node)
+    }
+
+    /// Add an entry for `name` to `dir`, with long name entries if the
+    /// name needs them
+    ///
+    /// The item at offset `ignore` doesn't count as being in the way,
+    /// so a rename can change the case of a name.
+    fn link(&mut self, dir: &Node, name: &str, mut entry: ShortEntry, ignore: Option<u32>) -> Result<Location> {
+        if !dir::valid_name(name) {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let (mut chain, mut data) = self.dir_data(dir)?;
+        let listing = dir::parse(&data);
+        if listing
+            .items
+            .iter()
+            .any(|item| Some(item.offset) != ignore && item.matches(name))
+        {
+            return Err(Error::new(Status::AlreadyExists));
+        }
+
+        let taken = |short: &[u8; 11]| listing.items.iter().any(|item| &item.entry.name() == short);
+        let long = match dir::short_name(name) {
+            Some((short, case)) if !taken(&short) => {
+                entry.set_name(short, case);
+                Vec::new()
+            }
+            _ => {
+                let alias = dir::short_alias(name, taken).ok_or(Error::new(Status::AlreadyExists))?;
+                entry.set_name(alias, 0);
+                dir::long_entries(name, dir::checksum(&alias))
+            }
+        };
+
+        // Free slots from the first free entry of a run long enough
+        let slots = long.len() + 1;
+        let mut start = None;
+        let mut run = 0;
+        for (index, raw) in data.chunks_exact(ENTRY_SIZE).enumerate() {
+            if raw[0] == DELETED || raw[0] == END {
+                run += 1;
+                if run == slots {
+                    start = Some((index + 1 - slots) * ENTRY_SIZE);
+                    break;
+                }
+            } else {
+                run = 0;
+            }
+        }
+        let start = match start {
+            Some(start) => start,
+            None => {
+                // Grow the directory by zeroed clusters
+                let needed = ((slots - run) * ENTRY_SIZE).div_ceil(self.cluster_size);
+                if data.len() + needed * self.cluster_size > MAX_DIR_SIZE {
+                    return Err(Error::new(Status::NoMemory));
+                }
+                let added = self.fat.allocate(needed as u32, chain.last().copied())?;
+                let zeros = vec![0u8; self.cluster_size];
+                for &cluster in &added {
+                    self.write_cluster(cluster, &zeros)?;
+                }
+                let start = data.len() - run * ENTRY_SIZE;
+                chain.extend(added);
+                data.resize(chain.len() * self.cluster_size, 0);
+                start
+            }
+        };
+
+        let end = start + slots * ENTRY_SIZE;
+        let found_end = data[..end].chunks_exact(ENTRY_SIZE).any(|raw| raw[0] == END);
+        for (slot, raw) in long.iter().chain(core::iter::once(&entry.0)).enumerate() {
+            let at = start + slot * ENTRY_SIZE;
+            data[at..at + ENTRY_SIZE].copy_from_slice(raw);
+        }
+        // Entries after the old end marker were never meant to be read;
+        // keep the end marker right after the new entry
+        let mut touched = end;
+        if found_end && end < data.len() {
+            data[end] = END;
+            touched += ENTRY_SIZE;
+        }
+        self.write_dir(&chain, &data, start, touched)?;
+        Ok(Location {
+            dir: dir.cluster(),
+            offset: (end - ENTRY_SIZE) as u32,
+        })
+    }
+
+    /// Mark an item's entries free
+    fn unlink(&mut self, dir: &Node, item: &Item) -> Result<()> {
+        let (chain, mut data) = self.dir_data(dir)?;
+        let (start, end) = (item.first_slot as usize, item.offset as usize + ENTRY_SIZE);
+        for raw in data[start..end].chunks_exact_mut(ENTRY_SIZE) {
+            raw[0] = DELETED;
+        }
+        self.write_dir(&chain, &data, start, end)
+    }
+
+    /// Create a file or, if `directory`, a directory called `name` in `dir`
+    pub fn create(&mut self, dir: &Node, name: &str, directory: bool) -> Result<Node> {
+        if !dir.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        if !directory {
+            let entry = ShortEntry::new([b' '; 11], 0, attr::ARCHIVE, 0, 0, self.now);
+            let location = self.link(dir, name, entry, None)?;
+            return self.lookup_at(location);
+        }
+
+        let cluster = self.fat.allocate(1, None)?[0];
+        let entry = ShortEntry::new([b' '; 11], 0, attr::DIRECTORY, cluster, 0, self.now);
+        let location = match self.link(dir, name, entry, None) {
+            Ok(location) => location,
+            Err(e) => {
+                self.fat.set(cluster, FREE);
+                return Err(e);
+            }
+        };
+
+        // `..` of a directory in the root points at cluster 0
+        let parent = if dir.location.is_none() { 0 } else { dir.cluster() };
+        let mut data = vec![0u8; self.cluster_size];
+        data[..ENTRY_SIZE].copy_from_slice(&ShortEntry::new(DOT, 0, attr::DIRECTORY, cluster, 0, self.now).0);
+        data[ENTRY_SIZE..2 * ENTRY_SIZE]
+            .copy_from_slice(&ShortEntry::new(DOTDOT, 0, attr::DIRECTORY, parent, 0, self.now).0);
+        self.write_cluster(cluster, &data)?;
+        self.lookup_at(location)
+    }
+
+    /// The node whose entry is at `location`
+    pub fn lookup_at(&mut self, location: Location) -> Result<Node> {
+        let chain = self.fat.chain(location.dir)?;
+        let offset = location.offset as usize;
+        let cluster = *chain
+            .get(offset / self.cluster_size)
+            .ok_or(Error::new(Status::IoError))?;
+        let mut data = vec![0u8; self.cluster_size];
+        self.read_cluster(cluster, &mut data)?;
+        let within = offset % self.cluster_size;
+        Ok(Node {
+            entry: ShortEntry(data[within..within + ENTRY_SIZE].try_into().unwrap()),
+            location: Some(location),
+        })
+    }
+
+    /// Remove the file or empty directory called `name` from `dir`
+    pub fn remove(&mut self, dir: &Node, name: &str) -> Result<()> {
+        let listing = self.list(dir)?;
+        let item = find(&listing.items, name).ok_or(Error::new(Status::NotFound))?;
+        let node = Node {
+            entry: item.entry,
+            location: None,
+        };
+        if node.read_only() {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        if node.is_dir() && !self.list(&node)?.items.is_empty() {
+            return Err(Error::new(Status::BadState));
+        }
+
+        self.unlink(dir, item)?;
+        self.fat.free_chain(node.cluster())
+    }
+
+    /// Move `from_name` in directory `from` to `to_name` in directory `to`
+    ///
+    /// Fails with `AlreadyExists` if `to_name` exists, unless it names
+    /// the node being moved, and with `InvalidArgs` if a directory would
+    /// move into itself.
+    pub fn rename(&mut self, from: &Node, from_name: &str, to: &Node, to_name: &str) -> Result<()> {
+        let listing = self.list(from)?;
+        let item = find(&listing.items, from_name).ok_or(Error::new(Status::NotFound))?;
+        if !to.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        let moved = from.cluster() != to.cluster();
+        if item.entry.is_dir() && moved && self.is_within(to, item.entry.cluster())? {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let ignore = (!moved).then_some(item.offset);
+        self.link(to, to_name, item.entry, ignore)?;
+        self.unlink(from, item)?;
+
+        if item.entry.is_dir() && moved {
+            let parent = if to.location.is_none() { 0 } else { to.cluster() };
+            let mut data = vec![0u8; self.cluster_size];
+            let cluster = item.entry.cluster();
+            self.read_cluster(cluster, &mut data)?;
+            for raw in data.chunks_exact_mut(ENTRY_SIZE) {
+                if raw[..11] == DOTDOT {
+                    let mut entry = ShortEntry(raw[..].try_into().unwrap());
+                    entry.set_cluster(parent);
+                    raw.copy_from_slice(&entry.0);
+                    break;
+                }
+            }
+            self.write_cluster(cluster, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Whether directory `dir` is directory `cluster` or inside it
+    fn is_within(&mut self, dir: &Node, cluster: u32) -> Result<bool> {
+        let root = self.bpb.root_cluster;
+        let mut current = dir.cluster();
+        for _ in 0..self.fat.len() {
+            if current == cluster {
+                return Ok(true);
+            }
+            if current == root {
+                return Ok(false);
+            }
+            let mut node = self.root();
+            node.entry.set_cluster(current);
+            current = match self.list(&node)?.dotdot {
+                Some(0) => root,
+                Some(parent) => parent,
+                None => return Err(Error::new(Status::IoError)),
+            };
+        }
+        Err(Error::new(Status::IoError))
+    }
+
+    /// Write the FAT and the FSInfo hints if they changed, then flush the
+    /// device
+    pub fn sync(&mut self) -> Result<()> {
+        if self.fat.is_dirty() {
+            self.fat.flush(&mut self.device, &self.bpb)?;
+            if let Some(index) = self.bpb.fsinfo_sector {
+                let mut sector = vec![0u8; self.bpb.bytes_per_sector as usize];
+                self.device.read_blocks(index as u64, &mut sector)?;
+                if FsInfo::parse(&sector).is_some() {
+                    let fsinfo = FsInfo {
+                        free_clusters: Some(self.fat.free_count()),
+                        next_free: Some(self.fat.next_free()),
+                    };
+                    fsinfo.store(&mut sector);
+                    self.device.write_blocks(index as u64, &sector)?;
+                }
+            }
+        }
+        self.device.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{self, RamDisk};
+
+    fn remount(mut volume: Volume<RamDisk>) -> Volume<RamDisk> {
+        volume.sync().unwrap();
+        Volume::mount(volume.into_device()).unwrap()
+    }
+
+    fn read_all(volume: &mut Volume<RamDisk>, node: &Node) -> Vec<u8> {
+        let mut data = vec![0u8; node.size() as usize];
+        assert_eq!(volume.read(node, 0, &mut data).unwrap(), data.len());
+        data
+    }
+
+    #[test]
+    fn test_mount() {
+        let mut volume = image::mount(1);
+        assert_eq!(volume.label().unwrap(), "");
+        assert_eq!(volume.cluster_size(), 512);
+        assert_eq!(volume.free_bytes(), volume.total_bytes() - 512);
+        let root = volume.root();
+        assert!(volume.list(&root).unwrap().items.is_empty());
+        assert_eq!(volume.lookup(&root, "missing").unwrap_err().status(), Status::NotFound);
+
+        // A volume must fit its device
+        let mut disk = image::mkfs(image::SECTORS, 1);
+        disk.0.truncate(disk.0.len() / 2);
+        assert_eq!(Volume::mount(disk).err().unwrap().status(), Status::NotSupported);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let mut volume = image::mount(1);
+        volume.set_time(1_700_000_000);
+        let root = volume.root();
+        let mut file = volume.create(&root, "hello.txt", false).unwrap();
+        assert_eq!((file.size(), file.cluster()), (0, 0));
+        let free = volume.free_bytes();
+
+        let data: Vec<u8> = (0..3000u32).map(|i| i as u8).collect();
+        assert_eq!(volume.write(&mut file, 0, &data).unwrap(), 3000);
+        assert_eq!(file.size(), 3000);
+        assert_eq!(volume.free_bytes(), free - 6 * 512);
+
+        // Unaligned reads, and reads past the end
+        let mut buf = [0u8; 700];
+        assert_eq!(volume.read(&file, 400, &mut buf).unwrap(), 700);
+        assert_eq!(&buf[..], &data[400..1100]);
+        assert_eq!(volume.read(&file, 2900, &mut buf).unwrap(), 100);
+        assert_eq!(volume.read(&file, 3000, &mut buf).unwrap(), 0);
+
+        // Writing past the end leaves zeros in the gap
+        volume.write(&mut file, 5000, b"tail").unwrap();
+        let contents = read_all(&mut volume, &file);
+        assert_eq!(contents.len(), 5004);
+        assert!(contents[3000..5000].iter().all(|&b| b == 0));
+        assert_eq!(&contents[5000..], b"tail");
+
+        // Shrinking frees clusters; growing again zeroes what was cut
+        volume.truncate(&mut file, 100).unwrap();
+        assert_eq!(volume.fat().chain(file.cluster()).unwrap().len(), 1);
+        volume.truncate(&mut file, 600).unwrap();
+        let contents = read_all(&mut volume, &file);
+        assert_eq!(&contents[..100], &data[..100]);
+        assert!(contents[100..].iter().all(|&b| b == 0));
+
+        volume.truncate(&mut file, 0).unwrap();
+        assert_eq!(file.cluster(), 0);
+        assert_eq!(volume.free_bytes(), free);
+
+        // The entry on disk follows the node
+        volume.write(&mut file, 0, &data).unwrap();
+        let mut volume = remount(volume);
+        let root = volume.root();
+        let file = volume.lookup(&root, "HELLO.TXT").unwrap();
+        assert_eq!(file.entry.modified(), 1_700_000_000);
+        assert_eq!(read_all(&mut volume, &file), data);
+
+        let mut dir = volume.root();
+        assert_eq!(volume.write(&mut dir, 0, b"x").unwrap_err().status(), Status::WrongType);
+    }
+
+    #[test]
+    fn test_directories() {
+        let mut volume = image::mount(1);
+        let root = volume.root();
+        let efi = volume.create(&root, "EFI", true).unwrap();
+        let boot = volume.create(&efi, "BOOT", true).unwrap();
+        let mut loader = volume.create(&boot, "BOOTX64.EFI", false).unwrap();
+        volume.write(&mut loader, 0, b"MZ").unwrap();
+        assert_eq!(
+            volume.create(&root, "efi", true).unwrap_err().status(),
+            Status::AlreadyExists
+        );
+
+        // Enough long names to need several clusters of entries
+        let names: Vec<String> = (0..40).map(|i| alloc::format!("Long File Name {}.txt", i)).collect();
+        for name in &names {
+            volume.create(&boot, name, false).unwrap();
+        }
+        let boot = volume.resolve(&["efi", "boot"]).unwrap();
+        let listing = volume.list(&boot).unwrap();
+        assert_eq!(listing.items.len(), 41);
+        assert_eq!(listing.orphans, 0);
+        assert_eq!(
+            (listing.dot, listing.dotdot),
+            (Some(boot.cluster()), Some(efi.cluster()))
+        );
+        assert!(volume.fat().chain(boot.cluster()).unwrap().len() > 1);
+        assert_eq!(
+            volume.lookup(&boot, "LONGFI~1.TXT").unwrap().entry.name(),
+            *b"LONGFI~1TXT"
+        );
+
+        assert_eq!(volume.remove(&root, "EFI").unwrap_err().status(), Status::BadState);
+        volume.remove(&boot, "long file name 7.txt").unwrap();
+        assert_eq!(
+            volume.lookup(&boot, "Long File Name 7.txt").unwrap_err().status(),
+            Status::NotFound
+        );
+
+        // Freed slots are used again
+        volume.create(&boot, "Long File Name 99.txt", false).unwrap();
+        assert_eq!(volume.list(&boot).unwrap().items.len(), 41);
+
+        let mut volume = remount(volume);
+        let root = volume.root();
+        let loader = volume.resolve(&["EFI", "BOOT", "bootx64.efi"]).unwrap();
+        assert_eq!(read_all(&mut volume, &loader), b"MZ");
+        assert_eq!(volume.resolve(&["EFI", "BOOT"]).unwrap().cluster(), boot.cluster());
+        assert_eq!(volume.lookup(&root, "BOOT").unwrap_err().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut volume = image::mount(1);
+        let root = volume.root();
+        let a = volume.create(&root, "a", true).unwrap();
+        let b = volume.create(&a, "b", true).unwrap();
+        let mut file = volume.create(&b, "notes.txt", false).unwrap();
+        volume.write(&mut file, 0, b"notes").unwrap();
+
+        // Across directories, and a change of case in place
+        volume.rename(&b, "notes.txt", &root, "Notes.md").unwrap();
+        volume.rename(&root, "Notes.md", &root, "NOTES.md").unwrap();
+        let listing = volume.list(&root).unwrap();
+        assert_eq!(
+            listing.items.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(),
+            ["a", "NOTES.md"]
+        );
+        let file = volume.lookup(&root, "notes.md").unwrap();
+        assert_eq!(read_all(&mut volume, &file), b"notes");
+        assert!(volume.list(&b).unwrap().items.is_empty());
+
+        // Moving a directory updates its `..`; not into itself
+        assert_eq!(
+            volume.rename(&root, "a", &b, "a").unwrap_err().status(),
+            Status::InvalidArgs
+        );
+        volume.rename(&a, "b", &root, "b").unwrap();
+        let b = volume.lookup(&root, "b").unwrap();
+        assert_eq!(volume.list(&b).unwrap().dotdot, Some(0));
+        assert_eq!(
+            volume.rename(&root, "b", &root, "a").unwrap_err().status(),
+            Status::AlreadyExists
+        );
+        assert_eq!(
+            volume.rename(&root, "x", &root, "y").unwrap_err().status(),
+            Status::NotFound
+        );
+    }
+
+    /// A volume populated the way Linux and mtools write one: an 8.3
+    /// name, a lower case 8.3 name kept with case bits, and a long name
+    #[test]
+    fn test_foreign_entries() {
+        let mut disk = image::mkfs(image::SECTORS, 8);
+        let bpb = BootSector::parse(&image::boot_sector(image::SECTORS, 8)).unwrap();
+        let root = bpb.cluster_sector(2) as usize * 512;
+        let long = "A File With A Long Name.dat";
+        let short = *b"AFILEW~1DAT";
+
+        let mut entries = vec![ShortEntry::new(*b"README  TXT", 0, attr::ARCHIVE, 3, 11, 1_600_000_000).0];
+        entries.push(ShortEntry::new(*b"HELLO   C  ", 0x18, attr::ARCHIVE, 0, 0, 1_600_000_000).0);
+        entries.extend(dir::long_entries(long, dir::checksum(&short)));
+        entries.push(ShortEntry::new(short, 0, attr::ARCHIVE | attr::READ_ONLY, 4, 5000, 1_600_000_000).0);
+        for (i, entry) in entries.iter().enumerate() {
+            disk.0[root + i * 32..root + (i + 1) * 32].copy_from_slice(entry);
+        }
+
+        let data = bpb.cluster_sector(3) as usize * 512;
+        disk.0[data..data + 11].copy_from_slice(b"Hello, FAT!");
+        let fat = bpb.fat_start(0) as usize * 512;
+        disk.0[fat + 12..fat + 16].copy_from_slice(&END_OF_CHAIN.to_le_bytes());
+        disk.0[fat + 16..fat + 20].copy_from_slice(&5u32.to_le_bytes());
+        disk.0[fat + 20..fat + 24].copy_from_slice(&END_OF_CHAIN.to_le_bytes());
+
+        let mut volume = Volume::mount(disk).unwrap();
+        let root = volume.root();
+        let names: Vec<String> = volume.list(&root).unwrap().items.into_iter().map(|i| i.name).collect();
+        assert_eq!(names, ["README.TXT", "hello.c", long]);
+
+        let readme = volume.lookup(&root, "readme.txt").unwrap();
+        assert_eq!(read_all(&mut volume, &readme), b"Hello, FAT!");
+        assert_eq!(readme.entry.modified(), 1_600_000_000);
+
+        let mut big = volume.lookup(&root, long).unwrap();
+        assert_eq!(volume.fat().chain(big.cluster()).unwrap(), [4, 5]);
+        assert_eq!(
+            volume.write(&mut big, 0, b"x").unwrap_err().status(),
+            Status::AccessDenied
+        );
+        assert_eq!(volume.remove(&root, long).unwrap_err().status(), Status::AccessDenied);
+    }
+}
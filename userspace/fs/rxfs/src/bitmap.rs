@@ -0,0 +1,264 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Bitmaps
+//!
+//! Inodes and blocks in use are tracked by bitmaps, kept whole in memory
+//! (32 KiB for each GiB of blocks) and changed on disk through the
+//! running transaction. What the running transaction frees can't be
+//! allocated again until it commits: the committed metadata may still
+//! point there, and file data written there would bypass the journal.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::layout::BLOCK_SIZE;
+use crate::store::Store;
+
+/// An inode or block bitmap
+pub struct Bitmap {
+    /// First block of the bitmap on the volume
+    start: u64,
+
+    /// Bits that count; the rest of the last block stays clear
+    len: u64,
+
+    bits: Vec<u8>,
+
+    /// Bits freed by the running transaction
+    held: Vec<u8>,
+
+    /// Runs freed by the running transaction, and how many of them the
+    /// running operation found
+    freed: Vec<(u64, u64)>,
+    mark: usize,
+
+    free: u64,
+
+    /// Where the next search starts
+    hint: u64,
+}
+
+impl Bitmap {
+    /// Load the bitmap of `len` bits at block `start`
+    pub fn load<D: BlockDevice>(store: &mut Store<D>, start: u64, len: u64) -> Result<Self> {
+        let blocks = len.div_ceil(BLOCK_SIZE as u64 * 8);
+        let mut bits = vec![0u8; blocks as usize * BLOCK_SIZE];
+        for (i, chunk) in bits.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            chunk.copy_from_slice(&store.read(start + i as u64)?);
+        }
+        let held = vec![0u8; bits.len()];
+        let mut bitmap = Self {
+            start,
+            len,
+            bits,
+            held,
+            freed: Vec::new(),
+            mark: 0,
+            free: 0,
+            hint: 0,
+        };
+        bitmap.count();
+        Ok(bitmap)
+    }
+
+    fn count(&mut self) {
+        let used: u64 = (0..self.len).filter(|&bit| self.is_set(bit)).count() as u64;
+        self.free = self.len - used;
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bits clear, those held until the running transaction commits
+    /// included
+    pub fn free(&self) -> u64 {
+        self.free
+    }
+
+    pub fn is_set(&self, bit: u64) -> bool {
+        self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+    }
+
+    fn available(&self, bit: u64) -> bool {
+        let (byte, mask) = ((bit / 8) as usize, 1 << (bit % 8));
+        (self.bits[byte] | self.held[byte]) & mask == 0
+    }
+
+    /// Set or clear `len` bits from `start`, on disk too
+    fn set<D: BlockDevice>(&mut self, store: &mut Store<D>, start: u64, len: u64, value: bool) -> Result<()> {
+        for bit in start..start + len {
+            let (byte, mask) = ((bit / 8) as usize, 1 << (bit % 8));
+            if value {
+                self.bits[byte] |= mask;
+            } else {
+                self.bits[byte] &= !mask;
+            }
+        }
+        if value {
+            self.free -= len;
+        } else {
+            self.free += len;
+        }
+
+        let bits_per_block = BLOCK_SIZE as u64 * 8;
+        for index in start / bits_per_block..=(start + len - 1) / bits_per_block {
+            let bytes = &self.bits[index as usize * BLOCK_SIZE..(index as usize + 1) * BLOCK_SIZE];
+            store.update(self.start + index, |data| data.copy_from_slice(bytes))?;
+        }
+        Ok(())
+    }
+
+    /// Take up to `want` clear bits in a row, searching from `goal`
+    ///
+    /// Returns the first bit and how many were taken. Fails with
+    /// `NoMemory` if every bit is set or held.
+    pub fn allocate<D: BlockDevice>(
+        &mut self,
+        store: &mut Store<D>,
+        want: u64,
+        goal: Option<u64>,
+    ) -> Result<(u64, u64)> {
+        let goal = goal.filter(|&goal| goal < self.len).unwrap_or(self.hint);
+        let first = (goal..self.len).chain(0..goal).find(|&bit| self.available(bit));
+        let first = first.ok_or(Error::new(Status::NoMemory))?;
+
+        let mut len = 1;
+        while len < want && first + len < self.len && self.available(first + len) {
+            len += 1;
+        }
+        self.set(store, first, len, true)?;
+        self.hint = first + len;
+        Ok((first, len))
+    }
+
+    /// Take bit `bit`, which must be clear
+    pub fn take<D: BlockDevice>(&mut self, store: &mut Store<D>, bit: u64) -> Result<()> {
+        if !self.available(bit) {
+            return Err(Error::new(Status::IoError));
+        }
+        self.set(store, bit, 1, true)
+    }
+
+    /// Clear `len` bits from `start`; they stay held until the running
+    /// transaction commits
+    pub fn release<D: BlockDevice>(&mut self, store: &mut Store<D>, start: u64, len: u64) -> Result<()> {
+        if start + len > self.len || (start..start + len).any(|bit| !self.is_set(bit)) {
+            return Err(Error::new(Status::IoError));
+        }
+        self.set(store, start, len, false)?;
+        for bit in start..start + len {
+            self.held[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        self.freed.push((start, len));
+        Ok(())
+    }
+
+    /// Start an operation
+    pub fn begin(&mut self) {
+        self.mark = self.freed.len();
+    }
+
+    /// Undo the running operation's changes, once the store put back
+    /// `blocks`
+    pub fn rollback<D: BlockDevice>(&mut self, store: &mut Store<D>, blocks: &[u64]) -> Result<()> {
+        for (start, len) in self.freed.split_off(self.mark) {
+            for bit in start..start + len {
+                self.held[(bit / 8) as usize] &= !(1 << (bit % 8));
+            }
+        }
+        let range = self.start..self.start + (self.bits.len() / BLOCK_SIZE) as u64;
+        for &block in blocks.iter().filter(|block| range.contains(block)) {
+            let at = (block - self.start) as usize * BLOCK_SIZE;
+            self.bits[at..at + BLOCK_SIZE].copy_from_slice(&store.read(block)?);
+        }
+        self.count();
+        Ok(())
+    }
+
+    /// Let the bits freed so far be allocated again, now that the running
+    /// transaction committed
+    pub fn committed(&mut self) {
+        self.held.fill(0);
+        self.freed.clear();
+        self.mark = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image;
+
+    #[test]
+    fn test_allocate_and_release() {
+        let mut store = image::store();
+        let sb = *store.superblock();
+        let mut bitmap = Bitmap::load(&mut store, sb.block_bitmap_start, sb.block_count).unwrap();
+        let used = sb.block_count - bitmap.free();
+        assert!(used > sb.data_start);
+
+        let (first, len) = bitmap.allocate(&mut store, 10, None).unwrap();
+        assert_eq!((first, len), (used, 10));
+        assert!(bitmap.is_set(first + 9));
+
+        // What is released stays held until the transaction commits
+        bitmap.release(&mut store, first, 5).unwrap();
+        assert_eq!(bitmap.allocate(&mut store, 3, Some(first)).unwrap(), (first + 10, 3));
+        bitmap.committed();
+        assert_eq!(bitmap.allocate(&mut store, 8, Some(first)).unwrap(), (first, 5));
+        assert_eq!(
+            bitmap.release(&mut store, first + 20, 1).unwrap_err().status(),
+            Status::IoError
+        );
+
+        // The bits reached the disk through the store
+        store.commit().unwrap();
+        let reloaded = Bitmap::load(&mut store, sb.block_bitmap_start, sb.block_count).unwrap();
+        assert_eq!(reloaded.free(), bitmap.free());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut store = image::store();
+        let sb = *store.superblock();
+        let mut bitmap = Bitmap::load(&mut store, sb.block_bitmap_start, sb.block_count).unwrap();
+        let (first, _) = bitmap.allocate(&mut store, 4, None).unwrap();
+        let free = bitmap.free();
+
+        store.begin();
+        bitmap.begin();
+        bitmap.release(&mut store, first, 4).unwrap();
+        bitmap.allocate(&mut store, 100, None).unwrap();
+        let blocks = store.end(false);
+        bitmap.rollback(&mut store, &blocks).unwrap();
+        assert_eq!(bitmap.free(), free);
+        assert!(bitmap.is_set(first));
+        assert_eq!(bitmap.allocate(&mut store, 1, Some(first)).unwrap(), (first + 4, 1));
+    }
+
+    #[test]
+    fn test_full() {
+        let mut store = image::store();
+        let sb = *store.superblock();
+        let mut bitmap = Bitmap::load(&mut store, sb.inode_bitmap_start, sb.inode_count as u64).unwrap();
+        while bitmap.free() > 0 {
+            bitmap.allocate(&mut store, 1000, None).unwrap();
+        }
+        assert_eq!(
+            bitmap.allocate(&mut store, 1, None).unwrap_err().status(),
+            Status::NoMemory
+        );
+    }
+}
@@ -0,0 +1,441 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Consistency Checks
+//!
+//! Walk the tree from the root and check every inode, extent and bucket
+//! on the way against the bitmaps and each other: blocks owned twice,
+//! entries a lookup wouldn't find, parents and entry counts that don't
+//! match, and bits set for nothing. Problems are reported, not repaired.
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use libddk::BlockDevice;
+use libsys::Result;
+
+use crate::bitmap::Bitmap;
+use crate::dir;
+use crate::layout::{kind, Inode, Superblock, BLOCK_SIZE, ROOT_INODE};
+use crate::store::Store;
+use crate::Volume;
+
+/// Something wrong with a volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// An entry names an inode that is free, out of range, of another
+    /// kind, or named already
+    BadInode { path: String, inode: u32 },
+
+    /// An inode in use is clear in the inode bitmap
+    UnallocatedInode { path: String, inode: u32 },
+
+    /// An extent reaches outside the data area or overlaps another
+    BadExtent { path: String, block: u64 },
+
+    /// A block owned by another node, or twice by this one
+    CrossLinked { path: String, block: u64 },
+
+    /// A block owned by a node is clear in the block bitmap
+    UnallocatedBlock { path: String, block: u64 },
+
+    /// A file maps blocks past its size, or a directory's size doesn't
+    /// match its buckets
+    SizeMismatch { path: String, size: u64, blocks: u64 },
+
+    /// A bucket is malformed, or holds a bad, duplicate or misplaced name
+    BadDirectory { path: String },
+
+    /// A node's parent isn't the directory holding it
+    BadParent { path: String },
+
+    /// A directory's entry count is wrong
+    EntryCountMismatch { path: String, recorded: u32, actual: u32 },
+
+    /// Blocks in use that no node owns
+    LostBlocks { count: u64 },
+
+    /// Inodes in use that no directory names
+    LostInodes { count: u64 },
+
+    /// Metadata blocks clear in the block bitmap
+    MetadataUnreserved,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::BadInode { path, inode } => write!(f, "{}: bad inode {}", path, inode),
+            Problem::UnallocatedInode { path, inode } => write!(f, "{}: inode {} is marked free", path, inode),
+            Problem::BadExtent { path, block } => write!(f, "{}: bad extent at block {}", path, block),
+            Problem::CrossLinked { path, block } => write!(f, "{}: cross-linked at block {}", path, block),
+            Problem::UnallocatedBlock { path, block } => write!(f, "{}: block {} is marked free", path, block),
+            Problem::SizeMismatch { path, size, blocks } => {
+                write!(f, "{}: size {} doesn't match {} blocks", path, size, blocks)
+            }
+            Problem::BadDirectory { path } => write!(f, "{}: bad directory bucket", path),
+            Problem::BadParent { path } => write!(f, "{}: wrong parent", path),
+            Problem::EntryCountMismatch { path, recorded, actual } => {
+                write!(f, "{}: {} entries recorded, actually {}", path, recorded, actual)
+            }
+            Problem::LostBlocks { count } => write!(f, "{} lost blocks", count),
+            Problem::LostInodes { count } => write!(f, "{} lost inodes", count),
+            Problem::MetadataUnreserved => write!(f, "metadata blocks are marked free"),
+        }
+    }
+}
+
+/// What a check found
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+    pub files: u32,
+    pub directories: u32,
+
+    /// Blocks owned by files and directories
+    pub used_blocks: u64,
+    pub free_blocks: u64,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn join(parent: &str, name: &str) -> String {
+    let mut path = String::from(parent);
+    if !path.ends_with('/') {
+        path.push('/');
+    }
+    path.push_str(name);
+    path
+}
+
+/// What the walk has found so far
+struct Walk<'a, D: BlockDevice> {
+    store: &'a mut Store<D>,
+    sb: Superblock,
+    inodes: Bitmap,
+    blocks: Bitmap,
+
+    /// Blocks owned by a node
+    owned: Vec<bool>,
+
+    /// Inodes named by an entry
+    seen: Vec<bool>,
+
+    report: Report,
+}
+
+impl<D: BlockDevice> Walk<'_, D> {
+    /// Inode `ino`, whatever its kind
+    fn inode(&mut self, ino: u32) -> Result<Inode> {
+        let (block, offset) = self.sb.inode_location(ino);
+        Ok(Inode::parse(&self.store.read(block)?[offset..]))
+    }
+
+    /// Claim the blocks of `inode` for `path`
+    fn claim(&mut self, inode: &Inode, path: &str) {
+        let mut end = 0;
+        for extent in &inode.extents {
+            let (start, len) = (extent.start as u64, extent.len as u64);
+            if extent.logical < end || start < self.sb.data_start || start + len > self.sb.block_count {
+                self.report.problems.push(Problem::BadExtent {
+                    path: String::from(path),
+                    block: start,
+                });
+                return;
+            }
+            end = extent.end();
+            for block in start..start + len {
+                if self.owned[block as usize] {
+                    self.report.problems.push(Problem::CrossLinked {
+                        path: String::from(path),
+                        block,
+                    });
+                    return;
+                }
+                self.owned[block as usize] = true;
+            }
+            if let Some(block) = (start..start + len).find(|&block| !self.blocks.is_set(block)) {
+                self.report.problems.push(Problem::UnallocatedBlock {
+                    path: String::from(path),
+                    block,
+                });
+            }
+        }
+    }
+
+    /// Check directory `ino` at `path`, returning the directories in it
+    fn directory(&mut self, ino: u32, parent: u32, path: &str) -> Result<Vec<(u32, String)>> {
+        let inode = self.inode(ino)?;
+        if inode.parent != parent {
+            self.report.problems.push(Problem::BadParent {
+                path: String::from(path),
+            });
+        }
+        self.claim(&inode, path);
+
+        let count = inode.size / BLOCK_SIZE as u64;
+        if inode.size % BLOCK_SIZE as u64 != 0 || !count.is_power_of_two() || inode.blocks() != count {
+            self.report.problems.push(Problem::SizeMismatch {
+                path: String::from(path),
+                size: inode.size,
+                blocks: inode.blocks(),
+            });
+            return Ok(Vec::new());
+        }
+        let mut buckets = Vec::new();
+        for index in 0..count as u32 {
+            match inode.map(index) {
+                Some(block) => buckets.push(self.store.read(block)?),
+                None => {
+                    self.report.problems.push(Problem::BadDirectory {
+                        path: String::from(path),
+                    });
+                    return Ok(Vec::new());
+                }
+            }
+        }
+
+        let mut names = BTreeSet::new();
+        let mut directories = Vec::new();
+        let mut entries = 0;
+        for (index, bucket) in buckets.iter().enumerate() {
+            if !dir::valid(bucket) {
+                self.report.problems.push(Problem::BadDirectory {
+                    path: String::from(path),
+                });
+                continue;
+            }
+            for entry in dir::entries(bucket) {
+                entries += 1;
+                let name = core::str::from_utf8(entry.name).unwrap_or_default();
+                let child = join(path, name);
+
+                // A lookup probes from the name's bucket through the
+                // overflowed ones
+                let first = (dir::hash(entry.name) as u64 % count) as usize;
+                let passed = (index + count as usize - first) % count as usize;
+                let reachable = (0..passed).all(|i| dir::overflowed(&buckets[(first + i) % count as usize]));
+                if !dir::valid_name(name) || !names.insert(entry.name) || !reachable {
+                    self.report.problems.push(Problem::BadDirectory { path: child.clone() });
+                }
+
+                let ino_ok = entry.inode > ROOT_INODE && entry.inode < self.sb.inode_count;
+                if !ino_ok || self.seen[entry.inode as usize] {
+                    self.report.problems.push(Problem::BadInode {
+                        path: child,
+                        inode: entry.inode,
+                    });
+                    continue;
+                }
+                self.seen[entry.inode as usize] = true;
+                let node = self.inode(entry.inode)?;
+                if node.kind != entry.kind || node.kind == kind::FREE {
+                    self.report.problems.push(Problem::BadInode {
+                        path: child,
+                        inode: entry.inode,
+                    });
+                    continue;
+                }
+                if !self.inodes.is_set(entry.inode as u64) {
+                    self.report.problems.push(Problem::UnallocatedInode {
+                        path: child.clone(),
+                        inode: entry.inode,
+                    });
+                }
+
+                if node.is_dir() {
+                    self.report.directories += 1;
+                    directories.push((entry.inode, child));
+                    continue;
+                }
+                self.report.files += 1;
+                if node.parent != ino {
+                    self.report.problems.push(Problem::BadParent { path: child.clone() });
+                }
+                self.claim(&node, &child);
+                let last = node.extents.last().map_or(0, |extent| extent.end() as u64);
+                if last > node.size.div_ceil(BLOCK_SIZE as u64) {
+                    self.report.problems.push(Problem::SizeMismatch {
+                        path: child,
+                        size: node.size,
+                        blocks: node.blocks(),
+                    });
+                }
+            }
+        }
+        if entries != inode.entries {
+            self.report.problems.push(Problem::EntryCountMismatch {
+                path: String::from(path),
+                recorded: inode.entries,
+                actual: entries,
+            });
+        }
+        Ok(directories)
+    }
+}
+
+/// Check a volume
+///
+/// Syncs it first, so what is checked is what is on the device.
+pub fn check<D: BlockDevice>(volume: &mut Volume<D>) -> Result<Report> {
+    volume.sync()?;
+    let sb = *volume.superblock();
+    let store = volume.store_mut();
+    let inodes = Bitmap::load(store, sb.inode_bitmap_start, sb.inode_count as u64)?;
+    let blocks = Bitmap::load(store, sb.block_bitmap_start, sb.block_count)?;
+    let mut walk = Walk {
+        store,
+        sb,
+        inodes,
+        blocks,
+        owned: vec![false; sb.block_count as usize],
+        seen: vec![false; sb.inode_count as usize],
+        report: Report::default(),
+    };
+    if (0..sb.data_start).any(|block| !walk.blocks.is_set(block)) {
+        walk.report.problems.push(Problem::MetadataUnreserved);
+    }
+
+    // Directories to visit: inode, parent and path
+    walk.seen[ROOT_INODE as usize] = true;
+    let mut pending = vec![(ROOT_INODE, ROOT_INODE, String::from("/"))];
+    while let Some((ino, parent, path)) = pending.pop() {
+        for (child, child_path) in walk.directory(ino, parent, &path)? {
+            pending.push((child, ino, child_path));
+        }
+    }
+
+    let lost = (sb.data_start..sb.block_count)
+        .filter(|&block| walk.blocks.is_set(block) && !walk.owned[block as usize])
+        .count() as u64;
+    if lost > 0 {
+        walk.report.problems.push(Problem::LostBlocks { count: lost });
+    }
+    let lost = (ROOT_INODE + 1..sb.inode_count)
+        .filter(|&ino| walk.inodes.is_set(ino as u64) && !walk.seen[ino as usize])
+        .count() as u64;
+    if lost > 0 {
+        walk.report.problems.push(Problem::LostInodes { count: lost });
+    }
+    walk.report.used_blocks = walk.owned.iter().filter(|&&owned| owned).count() as u64;
+    walk.report.free_blocks = walk.blocks.free();
+    Ok(walk.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{self, RamDisk};
+
+    /// A volume with a few directories and files
+    fn populated() -> Volume<RamDisk> {
+        let mut volume = image::mount();
+        let efi = volume.create(ROOT_INODE, "EFI", kind::DIRECTORY).unwrap();
+        let boot = volume.create(efi, "BOOT", kind::DIRECTORY).unwrap();
+        let loader = volume.create(boot, "BOOTX64.EFI", kind::FILE).unwrap();
+        volume.write(loader, 0, &[0xAA; 10_000]).unwrap();
+        let notes = volume.create(ROOT_INODE, "Release notes.txt", kind::FILE).unwrap();
+        volume.write(notes, 0, b"notes").unwrap();
+        volume.create(ROOT_INODE, "empty", kind::FILE).unwrap();
+        volume
+    }
+
+    /// Change inode `ino` on the device
+    fn patch(volume: Volume<RamDisk>, ino: u32, change: impl FnOnce(&mut Inode)) -> Volume<RamDisk> {
+        let sb = *volume.superblock();
+        let mut disk = volume.into_device();
+        let (block, offset) = sb.inode_location(ino);
+        let at = block as usize * BLOCK_SIZE + offset;
+        let mut inode = Inode::parse(&disk.0[at..]);
+        change(&mut inode);
+        inode.store(&mut disk.0[at..]);
+        Volume::mount(disk).unwrap()
+    }
+
+    #[test]
+    fn test_clean() {
+        let mut volume = image::mount();
+        let report = check(&mut volume).unwrap();
+        assert!(report.is_clean(), "{:?}", report.problems);
+        assert_eq!(report.used_blocks, 1);
+
+        let mut volume = populated();
+        let report = check(&mut volume).unwrap();
+        assert!(report.is_clean(), "{:?}", report.problems);
+        assert_eq!((report.files, report.directories), (3, 2));
+        assert_eq!(report.used_blocks, 1 + 2 + 3 + 1);
+        assert_eq!(report.free_blocks * BLOCK_SIZE as u64, volume.free_bytes());
+    }
+
+    #[test]
+    fn test_problems() {
+        let mut volume = populated();
+        let loader = volume.resolve(&["EFI", "BOOT", "BOOTX64.EFI"]).unwrap();
+        let notes = volume.lookup(ROOT_INODE, "Release notes.txt").unwrap();
+        let empty = volume.lookup(ROOT_INODE, "empty").unwrap();
+        let boot = volume.resolve(&["EFI", "BOOT"]).unwrap();
+        let shared = volume.inode(loader).unwrap().extents[0];
+        volume.sync().unwrap();
+
+        // "empty" shares the loader's blocks, the notes' size is wrong and
+        // BOOT has the wrong parent and count
+        let volume = patch(volume, empty, |inode| {
+            inode.size = 4096;
+            inode.extents = vec![crate::layout::Extent {
+                logical: 0,
+                len: 1,
+                ..shared
+            }];
+        });
+        let volume = patch(volume, notes, |inode| inode.size = 0);
+        let mut volume = patch(volume, boot, |inode| {
+            inode.parent = ROOT_INODE;
+            inode.entries = 2;
+        });
+        let report = check(&mut volume).unwrap();
+        let problems = report.problems;
+        assert!(problems.contains(&Problem::SizeMismatch {
+            path: String::from("/Release notes.txt"),
+            size: 0,
+            blocks: 1
+        }));
+        assert!(problems.contains(&Problem::BadParent {
+            path: String::from("/EFI/BOOT")
+        }));
+        assert!(problems.contains(&Problem::EntryCountMismatch {
+            path: String::from("/EFI/BOOT"),
+            recorded: 2,
+            actual: 1
+        }));
+        assert!(problems
+            .iter()
+            .any(|problem| matches!(problem, Problem::CrossLinked { block, .. } if *block == shared.start as u64)));
+        // The claim stops at the cross-link, losing the rest of the run
+        assert!(problems.contains(&Problem::LostBlocks { count: 2 }));
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+
+        // A file removed from its directory behind the volume's back
+        let mut volume = image::mount();
+        let file = volume.create(ROOT_INODE, "file", kind::FILE).unwrap();
+        volume.write(file, 0, &[1; 5000]).unwrap();
+        volume.sync().unwrap();
+        let mut volume = patch(volume, ROOT_INODE, |inode| inode.entries = 0);
+        let bucket = volume.inode(ROOT_INODE).unwrap().extents[0].start as u64;
+        volume.store_mut().put(bucket, &dir::empty());
+        let problems = check(&mut volume).unwrap().problems;
+        assert_eq!(
+            problems,
+            [Problem::LostBlocks { count: 2 }, Problem::LostInodes { count: 1 }]
+        );
+    }
+}
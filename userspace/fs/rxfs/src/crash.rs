@@ -0,0 +1,267 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Crash Consistency
+//!
+//! Runs a workload over a disk that loses power after its Nth write, for
+//! every N the workload reaches. Writes since the last flush may or may
+//! not have landed when the power went, so each cut is tried with none
+//! of them, all of them, and every other one. After each cut the volume
+//! must mount, replaying its journal, check clean, and hold the tree of
+//! the last sync that finished or of the one in flight.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::{BlockDevice, BlockInfo};
+use libsys::{Error, Result, Status};
+
+use crate::image;
+use crate::layout::{kind, BLOCK_SIZE, ROOT_INODE};
+use crate::Volume;
+
+/// Blocks of the volume (4 MiB)
+const BLOCKS: usize = 1024;
+
+/// Which writes since the last flush survive a power cut
+#[derive(Debug, Clone, Copy)]
+enum Keep {
+    None,
+    All,
+    Alternate,
+}
+
+/// A disk whose writes are only durable once flushed
+struct CrashDisk {
+    durable: Vec<u8>,
+
+    /// Writes since the last flush, in order: byte offset and data
+    volatile: Vec<(usize, Vec<u8>)>,
+
+    /// Writes before the power goes, if it is going to
+    budget: Option<usize>,
+    writes: usize,
+}
+
+impl CrashDisk {
+    fn new(durable: Vec<u8>, budget: Option<usize>) -> Self {
+        Self {
+            durable,
+            volatile: Vec::new(),
+            budget,
+            writes: 0,
+        }
+    }
+
+    fn powered(&self) -> bool {
+        self.budget.is_none_or(|budget| self.writes < budget)
+    }
+
+    /// What the disk holds once the power is back
+    fn image(self, keep: Keep) -> Vec<u8> {
+        let mut image = self.durable;
+        for (index, (at, data)) in self.volatile.iter().enumerate() {
+            let kept = match keep {
+                Keep::None => false,
+                Keep::All => true,
+                Keep::Alternate => index % 2 == 0,
+            };
+            if kept {
+                image[*at..at + data.len()].copy_from_slice(data);
+            }
+        }
+        image
+    }
+}
+
+impl BlockDevice for CrashDisk {
+    fn info(&self) -> BlockInfo {
+        BlockInfo {
+            block_size: 512,
+            max_transfer: 0,
+            block_count: (self.durable.len() / 512) as u64,
+        }
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let start = lba as usize * 512;
+        let end = start + buf.len();
+        buf.copy_from_slice(&self.durable[start..end]);
+        for (at, data) in &self.volatile {
+            let (from, to) = (start.max(*at), end.min(at + data.len()));
+            if from < to {
+                buf[from - start..to - start].copy_from_slice(&data[from - at..to - at]);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        if !self.powered() {
+            return Err(Error::new(Status::IoError));
+        }
+        self.writes += 1;
+        self.volatile.push((lba as usize * 512, buf.to_vec()));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.powered() {
+            return Err(Error::new(Status::IoError));
+        }
+        for (at, data) in self.volatile.drain(..) {
+            self.durable[at..at + data.len()].copy_from_slice(&data);
+        }
+        Ok(())
+    }
+}
+
+/// Paths and contents of every node; `None` for directories
+type Tree = BTreeMap<String, Option<Vec<u8>>>;
+
+fn walk<D: BlockDevice>(volume: &mut Volume<D>, dir: u32, path: &str, tree: &mut Tree) -> Result<()> {
+    let mut cookie = 0;
+    loop {
+        let children = volume.list(dir, cookie, 64)?;
+        let Some(last) = children.last() else {
+            return Ok(());
+        };
+        cookie = last.cookie;
+        for child in children {
+            let child_path = format!("{}/{}", path, child.name);
+            if child.kind == kind::DIRECTORY {
+                tree.insert(child_path.clone(), None);
+                walk(volume, child.inode, &child_path, tree)?;
+            } else {
+                let mut data = vec![0u8; child.size as usize];
+                volume.read(child.inode, 0, &mut data)?;
+                tree.insert(child_path, Some(data));
+            }
+        }
+    }
+}
+
+fn tree<D: BlockDevice>(volume: &mut Volume<D>) -> Result<Tree> {
+    let mut tree = Tree::new();
+    walk(volume, ROOT_INODE, "", &mut tree)?;
+    Ok(tree)
+}
+
+fn pattern(seed: u8, len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+/// Run the workload, syncing after every step and taking the tree once
+/// each sync finishes
+fn workload<D: BlockDevice>(volume: &mut Volume<D>, trees: &mut Vec<Tree>) -> Result<()> {
+    let boot = volume.create(ROOT_INODE, "boot", kind::DIRECTORY)?;
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    let kernel = volume.create(boot, "kernel.efi", kind::FILE)?;
+    volume.write(kernel, 0, &pattern(1, 20_000))?;
+    let etc = volume.create(ROOT_INODE, "etc", kind::DIRECTORY)?;
+    let config = volume.create(etc, "config", kind::FILE)?;
+    volume.write(config, 0, b"root=/dev/nvme0n1p2")?;
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    // Appending rewrites the block holding the old end in place
+    volume.write(kernel, 20_000, &pattern(2, 10_000))?;
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    // Enough names to double the directory's buckets
+    for index in 0..120 {
+        let name = format!("service-{:03}.conf, with a longer name", index);
+        let file = volume.create(etc, &name, kind::FILE)?;
+        volume.write(file, 0, name.as_bytes())?;
+    }
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    volume.rename(etc, "config", boot, "config")?;
+    volume.truncate(kernel, 5000)?;
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    for index in (0..120).step_by(3) {
+        volume.remove(etc, &format!("service-{:03}.conf, with a longer name", index))?;
+    }
+    volume.write(config, 100_000, b"sparse")?;
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    volume.rename(ROOT_INODE, "etc", boot, "etc")?;
+    volume.remove(boot, "kernel.efi")?;
+    volume.sync()?;
+    trees.push(tree(volume)?);
+
+    // Reuses the blocks freed by the last step
+    let image = volume.create(ROOT_INODE, "initrd", kind::FILE)?;
+    for chunk in 0..8 {
+        volume.write(image, chunk * 32_768, &pattern(chunk as u8, 32_768))?;
+    }
+    volume.sync()?;
+    trees.push(tree(volume)?);
+    Ok(())
+}
+
+#[test]
+fn test_power_cuts() {
+    let formatted = image::volume(BLOCKS).into_device().0;
+    assert_eq!(formatted.len(), BLOCKS * BLOCK_SIZE);
+
+    // A run without a cut gives the trees to expect, and how many writes
+    // there are to cut after
+    let mut volume = Volume::mount(CrashDisk::new(formatted.clone(), None)).unwrap();
+    let mut expected = vec![tree(&mut volume).unwrap()];
+    workload(&mut volume, &mut expected).unwrap();
+    assert!(crate::check(&mut volume).unwrap().is_clean());
+    let writes = volume.into_device().writes;
+
+    let (mut old, mut new) = (0, 0);
+    for budget in 0..writes {
+        for keep in [Keep::None, Keep::All, Keep::Alternate] {
+            let mut volume = Volume::mount(CrashDisk::new(formatted.clone(), Some(budget))).unwrap();
+            let mut trees = vec![expected[0].clone()];
+            assert!(workload(&mut volume, &mut trees).is_err());
+            let synced = trees.len() - 1;
+
+            let image = volume.into_device().image(keep);
+            let mut volume = Volume::mount(image::RamDisk(image)).unwrap();
+            let report = crate::check(&mut volume).unwrap();
+            assert!(
+                report.is_clean(),
+                "cut after {} writes, {:?}: {:?}",
+                budget,
+                keep,
+                report.problems
+            );
+            let found = tree(&mut volume).unwrap();
+            if found == expected[synced] {
+                old += 1;
+            } else if found == expected[synced + 1] {
+                new += 1;
+            } else {
+                panic!(
+                    "cut after {} writes, {:?}: tree matches neither sync {} nor {}",
+                    budget,
+                    keep,
+                    synced,
+                    synced + 1
+                );
+            }
+        }
+    }
+    // Cuts landed both before and after commit blocks became durable
+    assert!(old > 0 && new > 0);
+}
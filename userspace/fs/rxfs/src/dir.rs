@@ -0,0 +1,233 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Directory Buckets
+//!
+//! A directory's data is an array of 4 KiB buckets, a power of two of
+//! them. A name goes into the bucket its hash picks or, if that one is
+//! full, the next one with room, wrapping around. Buckets passed over are
+//! marked as overflowed, so a lookup that misses in a bucket only goes on
+//! to the next if the bucket is marked. When no bucket has room the
+//! directory doubles and every entry is hashed again.
+//!
+//! A bucket starts with an 8-byte header: bytes in use (u16, the header
+//! included), flags (u16) and the entry count (u16). Entries follow, each
+//! an inode number (u32), the inode's kind (u8), the name's length (u8)
+//! and the name, UTF-8.
+
+use libsys::{Error, Result, Status};
+
+use crate::layout::{u16_at, u32_at, BLOCK_SIZE};
+
+pub const HEADER_SIZE: usize = 8;
+
+/// Bytes of an entry before its name
+const ENTRY_HEADER: usize = 6;
+
+/// Most buckets a directory has
+///
+/// Doubling from half as many must fit in one transaction.
+pub const MAX_BUCKETS: u32 = 64;
+
+/// Longest name, in bytes
+pub const MAX_NAME: usize = 255;
+
+/// Bucket flag set when an insert passed over the bucket
+const OVERFLOW: u16 = 1 << 0;
+
+/// FNV-1a hash of a name
+pub fn hash(name: &[u8]) -> u32 {
+    name.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Whether `name` can name a node
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_NAME && name != "." && name != ".." && !name.contains(['/', '\0'])
+}
+
+/// An entry in a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    pub inode: u32,
+    pub kind: u16,
+    pub name: &'a [u8],
+
+    /// Byte offset of the entry in its bucket
+    pub offset: usize,
+}
+
+impl Entry<'_> {
+    /// Byte offset of the entry after this one
+    pub fn end(&self) -> usize {
+        self.offset + ENTRY_HEADER + self.name.len()
+    }
+}
+
+/// An empty bucket
+pub fn empty() -> [u8; BLOCK_SIZE] {
+    let mut bucket = [0u8; BLOCK_SIZE];
+    bucket[0..2].copy_from_slice(&(HEADER_SIZE as u16).to_le_bytes());
+    bucket
+}
+
+/// Bytes of the bucket in use
+fn used(bucket: &[u8]) -> usize {
+    (u16_at(bucket, 0) as usize).clamp(HEADER_SIZE, BLOCK_SIZE)
+}
+
+pub fn count(bucket: &[u8]) -> u16 {
+    u16_at(bucket, 4)
+}
+
+pub fn overflowed(bucket: &[u8]) -> bool {
+    u16_at(bucket, 2) & OVERFLOW != 0
+}
+
+pub fn set_overflowed(bucket: &mut [u8]) {
+    let flags = u16_at(bucket, 2) | OVERFLOW;
+    bucket[2..4].copy_from_slice(&flags.to_le_bytes());
+}
+
+/// Whether the bucket's header and entries are well formed
+pub fn valid(bucket: &[u8]) -> bool {
+    let used = u16_at(bucket, 0) as usize;
+    (HEADER_SIZE..=BLOCK_SIZE).contains(&used)
+        && entries(bucket).last().map_or(HEADER_SIZE, |entry| entry.end()) == used
+        && entries(bucket).count() == count(bucket) as usize
+}
+
+/// The entries of a bucket, from byte offset `from`
+///
+/// Stops at the first entry that runs past the bytes in use.
+pub fn entries_from(bucket: &[u8], from: usize) -> impl Iterator<Item = Entry<'_>> {
+    let used = used(bucket);
+    let mut offset = from.max(HEADER_SIZE);
+    core::iter::from_fn(move || {
+        if offset + ENTRY_HEADER > used {
+            return None;
+        }
+        let len = bucket[offset + 5] as usize;
+        if offset + ENTRY_HEADER + len > used {
+            return None;
+        }
+        let entry = Entry {
+            inode: u32_at(bucket, offset),
+            kind: bucket[offset + 4] as u16,
+            name: &bucket[offset + ENTRY_HEADER..offset + ENTRY_HEADER + len],
+            offset,
+        };
+        offset = entry.end();
+        Some(entry)
+    })
+}
+
+pub fn entries(bucket: &[u8]) -> impl Iterator<Item = Entry<'_>> {
+    entries_from(bucket, HEADER_SIZE)
+}
+
+/// The entry called `name`
+pub fn find<'a>(bucket: &'a [u8], name: &[u8]) -> Option<Entry<'a>> {
+    entries(bucket).find(|entry| entry.name == name)
+}
+
+/// Whether an entry called `name` fits
+pub fn has_room(bucket: &[u8], name: &[u8]) -> bool {
+    used(bucket) + ENTRY_HEADER + name.len() <= BLOCK_SIZE
+}
+
+/// Add an entry; fails with `NoMemory` if it doesn't fit
+pub fn insert(bucket: &mut [u8], inode: u32, kind: u16, name: &[u8]) -> Result<()> {
+    if name.len() > MAX_NAME || !has_room(bucket, name) {
+        return Err(Error::new(Status::NoMemory));
+    }
+    let at = used(bucket);
+    bucket[at..at + 4].copy_from_slice(&inode.to_le_bytes());
+    bucket[at + 4] = kind as u8;
+    bucket[at + 5] = name.len() as u8;
+    bucket[at + ENTRY_HEADER..at + ENTRY_HEADER + name.len()].copy_from_slice(name);
+
+    let used = (at + ENTRY_HEADER + name.len()) as u16;
+    let count = count(bucket) + 1;
+    bucket[0..2].copy_from_slice(&used.to_le_bytes());
+    bucket[4..6].copy_from_slice(&count.to_le_bytes());
+    Ok(())
+}
+
+/// Remove the entry at byte offset `offset`, closing the gap
+pub fn remove(bucket: &mut [u8], offset: usize) {
+    let Some(entry) = entries_from(bucket, offset).next() else {
+        return;
+    };
+    let (end, used) = (entry.end(), used(bucket));
+    bucket.copy_within(end..used, offset);
+    let used = used - (end - offset);
+    bucket[used..].fill(0);
+    let count = count(bucket).saturating_sub(1);
+    bucket[0..2].copy_from_slice(&(used as u16).to_le_bytes());
+    bucket[4..6].copy_from_slice(&count.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut bucket = empty();
+        insert(&mut bucket, 5, 1, b"kernel.efi").unwrap();
+        insert(&mut bucket, 6, 2, b"boot").unwrap();
+        insert(&mut bucket, 7, 1, b"config").unwrap();
+        assert!(valid(&bucket));
+        assert_eq!(count(&bucket), 3);
+        assert_eq!(
+            find(&bucket, b"boot").map(|entry| (entry.inode, entry.kind)),
+            Some((6, 2))
+        );
+        assert!(find(&bucket, b"BOOT").is_none());
+
+        let boot = find(&bucket, b"boot").unwrap().offset;
+        remove(&mut bucket, boot);
+        assert!(valid(&bucket));
+        let names: Vec<&[u8]> = entries(&bucket).map(|entry| entry.name).collect();
+        assert_eq!(names, [&b"kernel.efi"[..], b"config"]);
+    }
+
+    #[test]
+    fn test_full_bucket() {
+        let mut bucket = empty();
+        let name = [b'x'; MAX_NAME];
+        let mut inserted = 0;
+        while has_room(&bucket, &name) {
+            insert(&mut bucket, inserted + 2, 1, &name).unwrap();
+            inserted += 1;
+        }
+        assert_eq!(inserted, 15);
+        assert_eq!(
+            insert(&mut bucket, 99, 1, &name).unwrap_err().status(),
+            Status::NoMemory
+        );
+        assert!(insert(&mut bucket, 99, 1, b"short").is_ok());
+        assert!(valid(&bucket));
+
+        assert!(!overflowed(&bucket));
+        set_overflowed(&mut bucket);
+        assert!(overflowed(&bucket) && valid(&bucket));
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(valid_name("EFI"));
+        assert!(valid_name("Release notes.txt"));
+        assert!(!valid_name(""));
+        assert!(!valid_name(".."));
+        assert!(!valid_name("a/b"));
+        assert_ne!(hash(b"a"), hash(b"b"));
+        assert_eq!(hash(b""), 0x811c_9dc5);
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Block Addressing
+//!
+//! The volume is addressed in 4 KiB blocks, whatever the device's own
+//! block size; devices with larger blocks, or ones that don't divide
+//! 4 KiB, aren't supported.
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::layout::BLOCK_SIZE;
+
+/// A device addressed in volume blocks
+pub struct Disk<D: BlockDevice> {
+    device: D,
+
+    /// Device blocks in a volume block
+    scale: u64,
+
+    /// Volume blocks the device holds
+    blocks: u64,
+}
+
+impl<D: BlockDevice> Disk<D> {
+    /// Fails with `NotSupported` if the device's block size doesn't
+    /// divide the volume's
+    pub fn new(device: D) -> Result<Self> {
+        let info = device.info();
+        let size = info.block_size as usize;
+        if size == 0 || size > BLOCK_SIZE || !BLOCK_SIZE.is_multiple_of(size) {
+            return Err(Error::new(Status::NotSupported));
+        }
+        let scale = (BLOCK_SIZE / size) as u64;
+        Ok(Self {
+            device,
+            scale,
+            blocks: info.block_count / scale,
+        })
+    }
+
+    /// Volume blocks the device holds
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    pub fn into_device(self) -> D {
+        self.device
+    }
+
+    /// Read blocks from `block` on into `buf`, a whole number of blocks
+    pub fn read(&mut self, block: u64, buf: &mut [u8]) -> Result<()> {
+        self.check(block, buf.len())?;
+        self.device.read_blocks(block * self.scale, buf)
+    }
+
+    /// Write `buf`, a whole number of blocks, from `block` on
+    pub fn write(&mut self, block: u64, buf: &[u8]) -> Result<()> {
+        self.check(block, buf.len())?;
+        self.device.write_blocks(block * self.scale, buf)
+    }
+
+    /// Make completed writes durable
+    pub fn flush(&mut self) -> Result<()> {
+        self.device.flush()
+    }
+
+    fn check(&self, block: u64, len: usize) -> Result<()> {
+        let count = (len / BLOCK_SIZE) as u64;
+        if !len.is_multiple_of(BLOCK_SIZE) || block.checked_add(count).is_none_or(|end| end > self.blocks) {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        Ok(())
+    }
+}
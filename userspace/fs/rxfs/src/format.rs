@@ -0,0 +1,183 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Formatting
+//!
+//! Lays a fresh volume out over a whole device: one inode for every
+//! 16 KiB, a journal big enough for the largest transaction, and an empty
+//! root directory. The superblock is written last, once everything else
+//! is durable, so a format that doesn't finish leaves no volume behind.
+
+use alloc::vec;
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::dir;
+use crate::disk::Disk;
+use crate::journal;
+use crate::layout::{kind, Inode, Superblock, BLOCK_SIZE, INODES_PER_BLOCK, ROOT_INODE};
+use crate::Volume;
+
+/// Bytes of the volume for each inode
+const BYTES_PER_INODE: u64 = 16 * 1024;
+
+/// Data blocks a volume needs at the least
+const MIN_DATA_BLOCKS: u64 = 64;
+
+/// Blocks zeroed by one write
+const ZERO_CHUNK: u64 = 16;
+
+/// Label bytes the superblock holds
+const MAX_LABEL: usize = 32;
+
+/// The layout `format` gives a device of `blocks` blocks
+fn layout(blocks: u64, label: &str) -> Result<Superblock> {
+    let blocks = blocks.min(u32::MAX as u64);
+    let inodes = (blocks * BLOCK_SIZE as u64 / BYTES_PER_INODE).clamp(64, (u32::MAX - INODES_PER_BLOCK) as u64);
+    let inode_count = inodes.next_multiple_of(INODES_PER_BLOCK as u64) as u32;
+    if label.len() > MAX_LABEL {
+        return Err(Error::new(Status::InvalidArgs));
+    }
+    let mut padded = [0u8; MAX_LABEL];
+    padded[..label.len()].copy_from_slice(label.as_bytes());
+
+    let mut sb = Superblock {
+        block_count: blocks,
+        inode_count,
+        journal_start: 1,
+        journal_blocks: journal::MAX_BLOCKS as u32 + 2,
+        inode_bitmap_start: 0,
+        block_bitmap_start: 0,
+        inode_table_start: 0,
+        data_start: 0,
+        sequence: 1,
+        label: padded,
+    };
+    sb.inode_bitmap_start = sb.journal_start + sb.journal_blocks as u64;
+    sb.block_bitmap_start = sb.inode_bitmap_start + sb.inode_bitmap_blocks();
+    sb.inode_table_start = sb.block_bitmap_start + sb.block_bitmap_blocks();
+    sb.data_start = sb.inode_table_start + sb.inode_table_blocks();
+    if sb.data_start + MIN_DATA_BLOCKS > blocks {
+        return Err(Error::new(Status::NotSupported));
+    }
+    Ok(sb)
+}
+
+/// Write zeros over `count` blocks from `start`
+fn zero<D: BlockDevice>(disk: &mut Disk<D>, start: u64, count: u64) -> Result<()> {
+    let zeros = vec![0u8; ZERO_CHUNK as usize * BLOCK_SIZE];
+    let mut block = start;
+    while block < start + count {
+        let len = ZERO_CHUNK.min(start + count - block);
+        disk.write(block, &zeros[..len as usize * BLOCK_SIZE])?;
+        block += len;
+    }
+    Ok(())
+}
+
+/// Set the first `count` bits of a bitmap starting at block `start`
+fn mark<D: BlockDevice>(disk: &mut Disk<D>, start: u64, count: u64) -> Result<()> {
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let bits_per_block = BLOCK_SIZE as u64 * 8;
+    for index in 0..count.div_ceil(bits_per_block) {
+        block.fill(0);
+        let bits = (count - index * bits_per_block).min(bits_per_block);
+        for bit in 0..bits {
+            block[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        disk.write(start + index, &block)?;
+    }
+    Ok(())
+}
+
+/// Format `device` and mount the new volume
+///
+/// Fails with `NotSupported` if the device is too small or its block
+/// size doesn't divide 4 KiB, and with `InvalidArgs` if the label is
+/// longer than 32 bytes.
+pub fn format<D: BlockDevice>(device: D, label: &str, now: u64) -> Result<Volume<D>> {
+    let mut disk = Disk::new(device)?;
+    let sb = layout(disk.blocks(), label)?;
+
+    // An old descriptor with the new sequence number could replay
+    zero(&mut disk, sb.journal_start, 1)?;
+    zero(&mut disk, sb.inode_bitmap_start, sb.data_start - sb.inode_bitmap_start)?;
+
+    // Inode 0 is never used; the metadata and the root's bucket are
+    // in use
+    let root_bucket = sb.data_start;
+    mark(&mut disk, sb.inode_bitmap_start, ROOT_INODE as u64 + 1)?;
+    mark(&mut disk, sb.block_bitmap_start, root_bucket + 1)?;
+
+    let mut root = Inode {
+        kind: kind::DIRECTORY,
+        parent: ROOT_INODE,
+        size: BLOCK_SIZE as u64,
+        modified: now,
+        ..Default::default()
+    };
+    root.add_extent(0, root_bucket as u32, 1)?;
+    let (block, offset) = sb.inode_location(ROOT_INODE);
+    let mut data = vec![0u8; BLOCK_SIZE];
+    root.store(&mut data[offset..]);
+    disk.write(block, &data)?;
+    disk.write(root_bucket, &dir::empty())?;
+    disk.flush()?;
+
+    sb.store(&mut data);
+    disk.write(0, &data)?;
+    disk.flush()?;
+    Volume::mount(disk.into_device())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::RamDisk;
+
+    #[test]
+    fn test_layout() {
+        let sb = layout(4096, "root").unwrap();
+        assert_eq!(sb.inode_count, 1024);
+        assert_eq!(sb.journal_blocks, 342);
+        assert_eq!(sb.inode_bitmap_start, 343);
+        assert_eq!(sb.block_bitmap_start, 344);
+        assert_eq!(sb.inode_table_start, 345);
+        assert_eq!(sb.data_start, 345 + 64);
+        assert_eq!(sb.label(), "root");
+
+        assert_eq!(layout(300, "").unwrap_err().status(), Status::NotSupported);
+        assert_eq!(layout(4096, &"x".repeat(33)).unwrap_err().status(), Status::InvalidArgs);
+    }
+
+    #[test]
+    fn test_format() {
+        let disk = RamDisk(vec![0xA5; 4096 * BLOCK_SIZE]);
+        let mut volume = format(disk, "root", 1_700_000_000).unwrap();
+        assert_eq!(volume.label(), "root");
+        let sb = *volume.superblock();
+        assert_eq!(
+            volume.free_bytes(),
+            (sb.block_count - sb.data_start - 1) * BLOCK_SIZE as u64
+        );
+
+        let root = volume.inode(ROOT_INODE).unwrap();
+        assert!(root.is_dir());
+        assert_eq!(root.modified, 1_700_000_000);
+        assert!(volume.list(ROOT_INODE, 0, 10).unwrap().is_empty());
+        assert!(crate::check(&mut volume).unwrap().is_clean());
+
+        // Too small, and blocks larger than the volume's
+        assert_eq!(
+            format(RamDisk(vec![0; 300 * BLOCK_SIZE]), "", 0)
+                .err()
+                .unwrap()
+                .status(),
+            Status::NotSupported
+        );
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Test Images
+//!
+//! Freshly formatted volumes on a disk in memory with 512-byte blocks,
+//! the size NVMe and AHCI disks usually report.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::{BlockDevice, BlockInfo};
+use libsys::Result;
+
+use crate::disk::Disk;
+use crate::layout::BLOCK_SIZE;
+use crate::store::Store;
+use crate::Volume;
+
+/// Blocks of the default test image (16 MiB)
+pub const BLOCKS: usize = 4096;
+
+/// A disk in memory
+pub struct RamDisk(pub Vec<u8>);
+
+impl BlockDevice for RamDisk {
+    fn info(&self) -> BlockInfo {
+        BlockInfo {
+            block_size: 512,
+            max_transfer: 0,
+            block_count: (self.0.len() / 512) as u64,
+        }
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let start = lba as usize * 512;
+        buf.copy_from_slice(&self.0[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        let start = lba as usize * 512;
+        self.0[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// A freshly formatted volume of `blocks` blocks
+pub fn volume(blocks: usize) -> Volume<RamDisk> {
+    crate::format(RamDisk(vec![0; blocks * BLOCK_SIZE]), "", 0).unwrap()
+}
+
+/// A freshly formatted volume of the default size
+pub fn mount() -> Volume<RamDisk> {
+    volume(BLOCKS)
+}
+
+/// The store of a freshly formatted volume
+pub fn store() -> Store<RamDisk> {
+    Store::open(Disk::new(mount().into_device()).unwrap()).unwrap()
+}
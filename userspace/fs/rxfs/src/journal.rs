@@ -0,0 +1,162 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Metadata Journal
+//!
+//! Metadata blocks (bitmaps, inodes and directory buckets) only reach
+//! their home locations through the journal. A transaction is written to
+//! the start of the journal area as:
+//! - A descriptor block: sequence number, block count, and the home and
+//!   CRC32 of each block that follows
+//! - The blocks
+//! - A commit block, written only once everything before it is durable
+//!
+//! Once the commit block is durable the transaction is checkpointed: its
+//! blocks are written home and the superblock's sequence number moves
+//! past it. On mount, a transaction with the superblock's sequence number
+//! and an intact commit block is replayed; without one it never happened.
+//! Replaying a transaction whose checkpoint had finished writes the same
+//! blocks again, which is harmless.
+//!
+//! File data isn't journaled. It is written before the transaction that
+//! points at it commits, so metadata never points at blocks that were
+//! not written (ordered mode).
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::gpt::crc32;
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::disk::Disk;
+use crate::layout::{u32_at, u64_at, Superblock, BLOCK_SIZE};
+
+/// "RXJD" and "RXJC"
+const DESCRIPTOR: u32 = u32::from_le_bytes(*b"RXJD");
+const COMMIT: u32 = u32::from_le_bytes(*b"RXJC");
+
+/// Bytes before the list in a descriptor block
+const HEADER_SIZE: usize = 16;
+
+/// Bytes of a descriptor's list entry: home block and CRC32
+const ENTRY_SIZE: usize = 12;
+
+/// Most blocks a descriptor block lists
+pub const MAX_BLOCKS: usize = (BLOCK_SIZE - HEADER_SIZE) / ENTRY_SIZE;
+
+/// Most blocks a transaction in the journal of `sb` holds
+pub fn capacity(sb: &Superblock) -> usize {
+    MAX_BLOCKS.min(sb.journal_blocks as usize - 2)
+}
+
+fn header(block: &mut [u8], magic: u32, count: usize, sequence: u64) {
+    block[0..4].copy_from_slice(&magic.to_le_bytes());
+    block[4..8].copy_from_slice(&(count as u32).to_le_bytes());
+    block[8..16].copy_from_slice(&sequence.to_le_bytes());
+}
+
+/// Write the blocks of a transaction to the journal and commit it
+///
+/// `blocks` are home block numbers and contents. Fails with `NoMemory`
+/// if the journal can't hold them.
+pub fn commit<D: BlockDevice>(disk: &mut Disk<D>, sb: &Superblock, blocks: &[(u64, &[u8])]) -> Result<()> {
+    if blocks.len() > capacity(sb) {
+        return Err(Error::new(Status::NoMemory));
+    }
+
+    let mut descriptor = vec![0u8; BLOCK_SIZE];
+    header(&mut descriptor, DESCRIPTOR, blocks.len(), sb.sequence);
+    for (i, (home, data)) in blocks.iter().enumerate() {
+        let at = HEADER_SIZE + i * ENTRY_SIZE;
+        descriptor[at..at + 8].copy_from_slice(&home.to_le_bytes());
+        descriptor[at + 8..at + 12].copy_from_slice(&crc32(data).to_le_bytes());
+    }
+    disk.write(sb.journal_start, &descriptor)?;
+    for (i, (_, data)) in blocks.iter().enumerate() {
+        disk.write(sb.journal_start + 1 + i as u64, data)?;
+    }
+    disk.flush()?;
+
+    let mut commit = vec![0u8; BLOCK_SIZE];
+    header(&mut commit, COMMIT, blocks.len(), sb.sequence);
+    commit[16..20].copy_from_slice(&crc32(&descriptor).to_le_bytes());
+    let crc = crc32(&commit[..20]);
+    commit[20..24].copy_from_slice(&crc.to_le_bytes());
+    disk.write(sb.journal_start + 1 + blocks.len() as u64, &commit)?;
+    disk.flush()
+}
+
+/// Write committed blocks home, then move the superblock past their
+/// transaction
+pub fn checkpoint<D: BlockDevice>(disk: &mut Disk<D>, sb: &mut Superblock, blocks: &[(u64, &[u8])]) -> Result<()> {
+    for (home, data) in blocks {
+        disk.write(*home, data)?;
+    }
+    disk.flush()?;
+
+    let next = Superblock {
+        sequence: sb.sequence + 1,
+        ..*sb
+    };
+    let mut block = vec![0u8; BLOCK_SIZE];
+    next.store(&mut block);
+    disk.write(0, &block)?;
+    disk.flush()?;
+    *sb = next;
+    Ok(())
+}
+
+/// Home block numbers and contents
+type Blocks = Vec<(u64, Vec<u8>)>;
+
+/// The blocks of the committed transaction waiting in the journal, if
+/// there is one
+fn committed<D: BlockDevice>(disk: &mut Disk<D>, sb: &Superblock) -> Result<Option<Blocks>> {
+    let mut descriptor = vec![0u8; BLOCK_SIZE];
+    disk.read(sb.journal_start, &mut descriptor)?;
+    let count = u32_at(&descriptor, 4) as usize;
+    if u32_at(&descriptor, 0) != DESCRIPTOR || u64_at(&descriptor, 8) != sb.sequence || count > capacity(sb) {
+        return Ok(None);
+    }
+
+    let mut commit = vec![0u8; BLOCK_SIZE];
+    disk.read(sb.journal_start + 1 + count as u64, &mut commit)?;
+    let intact = u32_at(&commit, 0) == COMMIT
+        && u32_at(&commit, 4) as usize == count
+        && u64_at(&commit, 8) == sb.sequence
+        && u32_at(&commit, 16) == crc32(&descriptor)
+        && u32_at(&commit, 20) == crc32(&commit[..20]);
+    if !intact {
+        return Ok(None);
+    }
+
+    let mut blocks = Vec::with_capacity(count);
+    for i in 0..count {
+        let at = HEADER_SIZE + i * ENTRY_SIZE;
+        let home = u64_at(&descriptor, at);
+        let mut data = vec![0u8; BLOCK_SIZE];
+        disk.read(sb.journal_start + 1 + i as u64, &mut data)?;
+        // Written before a flush the commit block came after
+        if crc32(&data) != u32_at(&descriptor, at + 8) || home >= sb.block_count {
+            return Err(Error::new(Status::IoError));
+        }
+        blocks.push((home, data));
+    }
+    Ok(Some(blocks))
+}
+
+/// Replay the committed transaction in the journal, if there is one
+///
+/// Returns whether there was one.
+pub fn replay<D: BlockDevice>(disk: &mut Disk<D>, sb: &mut Superblock) -> Result<bool> {
+    let Some(blocks) = committed(disk, sb)? else {
+        return Ok(false);
+    };
+    let blocks: Vec<(u64, &[u8])> = blocks.iter().map(|(home, data)| (*home, &data[..])).collect();
+    checkpoint(disk, sb, &blocks)?;
+    Ok(true)
+}
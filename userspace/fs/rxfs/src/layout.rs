@@ -0,0 +1,395 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! On-Disk Layout
+//!
+//! An rxfs volume is an array of 4 KiB blocks, laid out as:
+//! - Block 0: the superblock
+//! - The metadata journal
+//! - The inode bitmap, one bit per inode
+//! - The block bitmap, one bit per block of the volume, the ones above
+//!   included
+//! - The inode table, 16 inodes of 256 bytes to a block
+//! - File and directory data
+//!
+//! Integers are little-endian. Inode 0 is never used and inode 1 is the
+//! root directory. A file's data is mapped by up to `MAX_EXTENTS` runs
+//! of blocks kept in its inode; logical blocks no extent maps are holes
+//! and read as zeros.
+
+use alloc::vec::Vec;
+
+use libddk::gpt::crc32;
+use libsys::{Error, Result, Status};
+
+/// Bytes in a block
+pub const BLOCK_SIZE: usize = 4096;
+
+/// Bits in a bitmap block
+pub const BITS_PER_BLOCK: u64 = BLOCK_SIZE as u64 * 8;
+
+pub const INODE_SIZE: usize = 256;
+pub const INODES_PER_BLOCK: u32 = (BLOCK_SIZE / INODE_SIZE) as u32;
+
+/// The root directory
+pub const ROOT_INODE: u32 = 1;
+
+/// Extents an inode holds
+pub const MAX_EXTENTS: usize = 18;
+
+/// "RUSTUXFS"
+const MAGIC: u64 = u64::from_le_bytes(*b"RUSTUXFS");
+const VERSION: u32 = 1;
+
+/// Bytes of the superblock the CRC covers, which follows them
+const SUPERBLOCK_SIZE: usize = 112;
+
+pub(crate) fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+pub(crate) fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+pub(crate) fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Where everything is on a volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superblock {
+    pub block_count: u64,
+    pub inode_count: u32,
+    pub journal_start: u64,
+    pub journal_blocks: u32,
+    pub inode_bitmap_start: u64,
+    pub block_bitmap_start: u64,
+    pub inode_table_start: u64,
+    pub data_start: u64,
+
+    /// Sequence number of the next journal transaction; anything older
+    /// in the journal has been checkpointed
+    pub sequence: u64,
+
+    /// Volume label, UTF-8, zero padded
+    pub label: [u8; 32],
+}
+
+impl Superblock {
+    /// Parse a superblock
+    ///
+    /// Fails with `NotSupported` if the block doesn't hold an rxfs
+    /// superblock, or `IoError` if it's damaged.
+    pub fn parse(block: &[u8]) -> Result<Self> {
+        if u64_at(block, 0) != MAGIC || u32_at(block, 8) != VERSION || u32_at(block, 12) != BLOCK_SIZE as u32 {
+            return Err(Error::new(Status::NotSupported));
+        }
+        if crc32(&block[..SUPERBLOCK_SIZE]) != u32_at(block, SUPERBLOCK_SIZE) {
+            return Err(Error::new(Status::IoError));
+        }
+
+        let sb = Self {
+            block_count: u64_at(block, 16),
+            inode_count: u32_at(block, 24),
+            journal_blocks: u32_at(block, 28),
+            journal_start: u64_at(block, 32),
+            inode_bitmap_start: u64_at(block, 40),
+            block_bitmap_start: u64_at(block, 48),
+            inode_table_start: u64_at(block, 56),
+            data_start: u64_at(block, 64),
+            sequence: u64_at(block, 72),
+            label: block[80..112].try_into().unwrap(),
+        };
+
+        // The regions must follow each other as `format` lays them out
+        let valid = sb.journal_start == 1
+            && sb.inode_bitmap_start == sb.journal_start + sb.journal_blocks as u64
+            && sb.block_bitmap_start == sb.inode_bitmap_start + sb.inode_bitmap_blocks()
+            && sb.inode_table_start == sb.block_bitmap_start + sb.block_bitmap_blocks()
+            && sb.data_start == sb.inode_table_start + sb.inode_table_blocks()
+            && sb.data_start < sb.block_count
+            && sb.block_count <= u32::MAX as u64
+            && sb.inode_count > ROOT_INODE
+            && sb.inode_count.is_multiple_of(INODES_PER_BLOCK);
+        if !valid {
+            return Err(Error::new(Status::IoError));
+        }
+        Ok(sb)
+    }
+
+    /// Write the superblock to the start of `block`
+    pub fn store(&self, block: &mut [u8]) {
+        block[..BLOCK_SIZE].fill(0);
+        block[0..8].copy_from_slice(&MAGIC.to_le_bytes());
+        block[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        block[12..16].copy_from_slice(&(BLOCK_SIZE as u32).to_le_bytes());
+        block[16..24].copy_from_slice(&self.block_count.to_le_bytes());
+        block[24..28].copy_from_slice(&self.inode_count.to_le_bytes());
+        block[28..32].copy_from_slice(&self.journal_blocks.to_le_bytes());
+        block[32..40].copy_from_slice(&self.journal_start.to_le_bytes());
+        block[40..48].copy_from_slice(&self.inode_bitmap_start.to_le_bytes());
+        block[48..56].copy_from_slice(&self.block_bitmap_start.to_le_bytes());
+        block[56..64].copy_from_slice(&self.inode_table_start.to_le_bytes());
+        block[64..72].copy_from_slice(&self.data_start.to_le_bytes());
+        block[72..80].copy_from_slice(&self.sequence.to_le_bytes());
+        block[80..112].copy_from_slice(&self.label);
+        let crc = crc32(&block[..SUPERBLOCK_SIZE]);
+        block[SUPERBLOCK_SIZE..SUPERBLOCK_SIZE + 4].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    pub fn inode_bitmap_blocks(&self) -> u64 {
+        (self.inode_count as u64).div_ceil(BITS_PER_BLOCK)
+    }
+
+    pub fn block_bitmap_blocks(&self) -> u64 {
+        self.block_count.div_ceil(BITS_PER_BLOCK)
+    }
+
+    pub fn inode_table_blocks(&self) -> u64 {
+        (self.inode_count / INODES_PER_BLOCK) as u64
+    }
+
+    /// Block holding inode `inode`, and the inode's offset in it
+    pub fn inode_location(&self, inode: u32) -> (u64, usize) {
+        let block = self.inode_table_start + (inode / INODES_PER_BLOCK) as u64;
+        (block, (inode % INODES_PER_BLOCK) as usize * INODE_SIZE)
+    }
+
+    /// The label, up to its first zero
+    pub fn label(&self) -> &str {
+        let len = self.label.iter().position(|&b| b == 0).unwrap_or(self.label.len());
+        core::str::from_utf8(&self.label[..len]).unwrap_or("")
+    }
+}
+
+/// Kinds of inode
+pub mod kind {
+    pub const FREE: u16 = 0;
+    pub const FILE: u16 = 1;
+    pub const DIRECTORY: u16 = 2;
+}
+
+/// Inode flags
+pub mod flags {
+    /// Writes, removal and renames are refused
+    pub const READ_ONLY: u16 = 1 << 0;
+}
+
+/// A run of blocks of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    /// First logical block of the file the extent maps
+    pub logical: u32,
+
+    /// First block of the run on the volume
+    pub start: u32,
+
+    pub len: u32,
+}
+
+impl Extent {
+    /// Logical block after the extent
+    pub fn end(&self) -> u32 {
+        self.logical + self.len
+    }
+}
+
+/// A file or directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inode {
+    /// One of `kind`
+    pub kind: u16,
+
+    /// Any of `flags`
+    pub flags: u16,
+
+    /// Directory holding this one; the root is its own parent
+    pub parent: u32,
+
+    /// Size in bytes; for directories, the bytes of their buckets
+    pub size: u64,
+
+    /// Last modification, in seconds since the Unix epoch
+    pub modified: u64,
+
+    /// Entries of a directory
+    pub entries: u32,
+
+    /// Sorted by logical block, not overlapping
+    pub extents: Vec<Extent>,
+}
+
+impl Inode {
+    pub fn parse(data: &[u8]) -> Self {
+        let count = (u16_at(data, 24) as usize).min(MAX_EXTENTS);
+        let extents = (0..count)
+            .map(|i| {
+                let at = 32 + i * 12;
+                Extent {
+                    logical: u32_at(data, at),
+                    start: u32_at(data, at + 4),
+                    len: u32_at(data, at + 8),
+                }
+            })
+            .collect();
+        Self {
+            kind: u16_at(data, 0),
+            flags: u16_at(data, 2),
+            parent: u32_at(data, 4),
+            size: u64_at(data, 8),
+            modified: u64_at(data, 16),
+            entries: u32_at(data, 28),
+            extents,
+        }
+    }
+
+    /// Write the inode to the start of `data`
+    pub fn store(&self, data: &mut [u8]) {
+        data[..INODE_SIZE].fill(0);
+        data[0..2].copy_from_slice(&self.kind.to_le_bytes());
+        data[2..4].copy_from_slice(&self.flags.to_le_bytes());
+        data[4..8].copy_from_slice(&self.parent.to_le_bytes());
+        data[8..16].copy_from_slice(&self.size.to_le_bytes());
+        data[16..24].copy_from_slice(&self.modified.to_le_bytes());
+        data[24..26].copy_from_slice(&(self.extents.len() as u16).to_le_bytes());
+        data[28..32].copy_from_slice(&self.entries.to_le_bytes());
+        for (i, extent) in self.extents.iter().enumerate() {
+            let at = 32 + i * 12;
+            data[at..at + 4].copy_from_slice(&extent.logical.to_le_bytes());
+            data[at + 4..at + 8].copy_from_slice(&extent.start.to_le_bytes());
+            data[at + 8..at + 12].copy_from_slice(&extent.len.to_le_bytes());
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == kind::DIRECTORY
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.flags & flags::READ_ONLY != 0
+    }
+
+    /// Block logical block `logical` is stored in, or `None` in a hole
+    pub fn map(&self, logical: u32) -> Option<u64> {
+        self.extents
+            .iter()
+            .find(|extent| (extent.logical..extent.end()).contains(&logical))
+            .map(|extent| (extent.start + (logical - extent.logical)) as u64)
+    }
+
+    /// Blocks the extents hold
+    pub fn blocks(&self) -> u64 {
+        self.extents.iter().map(|extent| extent.len as u64).sum()
+    }
+
+    /// Map logical blocks `logical..` to blocks `start..`, `len` of them
+    ///
+    /// Joins the run to the extent it continues, if any. Fails with
+    /// `NoMemory` if the inode has no room for another extent.
+    pub fn add_extent(&mut self, logical: u32, start: u32, len: u32) -> Result<()> {
+        let index = self.extents.partition_point(|extent| extent.logical < logical);
+        if let Some(previous) = index.checked_sub(1).map(|i| &mut self.extents[i]) {
+            if previous.end() == logical && previous.start + previous.len == start {
+                previous.len += len;
+                return Ok(());
+            }
+        }
+        if self.extents.len() == MAX_EXTENTS {
+            return Err(Error::new(Status::NoMemory));
+        }
+        self.extents.insert(index, Extent { logical, start, len });
+        Ok(())
+    }
+
+    /// Unmap logical blocks from `first` on, returning the runs of blocks
+    /// they were stored in
+    pub fn cut_extents(&mut self, first: u32) -> Vec<(u32, u32)> {
+        let mut freed = Vec::new();
+        self.extents.retain_mut(|extent| {
+            if extent.logical >= first {
+                freed.push((extent.start, extent.len));
+                false
+            } else if extent.end() > first {
+                let keep = first - extent.logical;
+                freed.push((extent.start + keep, extent.len - keep));
+                extent.len = keep;
+                true
+            } else {
+                true
+            }
+        });
+        freed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_superblock() {
+        let sb = Superblock {
+            block_count: 1024,
+            inode_count: 256,
+            journal_start: 1,
+            journal_blocks: 256,
+            inode_bitmap_start: 257,
+            block_bitmap_start: 258,
+            inode_table_start: 259,
+            data_start: 275,
+            sequence: 7,
+            label: *b"root\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+        };
+        let mut block = vec![0u8; BLOCK_SIZE];
+        sb.store(&mut block);
+        assert_eq!(Superblock::parse(&block).unwrap(), sb);
+        assert_eq!(sb.label(), "root");
+        assert_eq!(sb.inode_location(17), (260, 256));
+
+        block[20] ^= 1;
+        assert_eq!(Superblock::parse(&block).unwrap_err().status(), Status::IoError);
+        block[0] ^= 1;
+        assert_eq!(Superblock::parse(&block).unwrap_err().status(), Status::NotSupported);
+    }
+
+    #[test]
+    fn test_extents() {
+        let mut inode = Inode {
+            kind: kind::FILE,
+            ..Default::default()
+        };
+        inode.add_extent(0, 100, 4).unwrap();
+        inode.add_extent(4, 104, 2).unwrap();
+        inode.add_extent(10, 200, 1).unwrap();
+        inode.add_extent(6, 300, 1).unwrap();
+        assert_eq!(inode.extents.len(), 3);
+        assert_eq!(inode.map(5), Some(105));
+        assert_eq!(inode.map(6), Some(300));
+        assert_eq!(inode.map(8), None);
+        assert_eq!(inode.blocks(), 8);
+
+        let mut data = vec![0u8; INODE_SIZE];
+        inode.store(&mut data);
+        assert_eq!(Inode::parse(&data), inode);
+
+        assert_eq!(inode.cut_extents(3), [(103, 3), (300, 1), (200, 1)]);
+        assert_eq!(
+            inode.extents,
+            [Extent {
+                logical: 0,
+                start: 100,
+                len: 3
+            }]
+        );
+
+        for i in 1..MAX_EXTENTS as u32 {
+            inode.add_extent(i * 10, i * 100 + 1000, 1).unwrap();
+        }
+        assert_eq!(inode.add_extent(500, 9000, 1).unwrap_err().status(), Status::NoMemory);
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Rustux Native Filesystem (rxfs)
+//!
+//! A read/write filesystem for the root volume, on any DDK `BlockDevice`
+//! with a block size that divides 4 KiB:
+//! - Files mapped by up to 18 extents, allocated from a block bitmap
+//! - A fixed inode table, allocated from an inode bitmap
+//! - Hashed directories that double as they fill
+//! - A metadata journal, replayed on mount, so a power cut loses at most
+//!   the changes since the last sync and never leaves the volume broken
+//! - `check`, an fsck that reports what's wrong with a volume
+//!
+//! The volume starts with the superblock, then the journal, the bitmaps
+//! and the inode table; the data area takes the rest.
+//!
+//! `RxfsServer` serves a volume over the Vfs protocol from libvfs.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::BlockDevice;
+//!
+//! fn serve<D: BlockDevice>(disk: D, channel: &ipc::Channel) -> libsys::Result<()> {
+//!     let volume = rxfs::Volume::mount(disk)?;
+//!     rxfs::RxfsServer::new(volume).run(channel)
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod bitmap;
+pub mod check;
+pub mod dir;
+pub mod disk;
+pub mod format;
+pub mod journal;
+pub mod layout;
+pub mod server;
+pub mod store;
+pub mod volume;
+
+#[cfg(test)]
+mod crash;
+#[cfg(test)]
+mod image;
+
+// Re-export commonly used types
+pub use check::{check, Problem, Report};
+pub use format::format;
+pub use server::RxfsServer;
+pub use volume::{Child, Volume};
@@ -0,0 +1,404 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Vfs Server
+//!
+//! Serves a mounted volume over the Vfs protocol. Open nodes are held by
+//! inode number, so every open of a node sees the others' writes; a node
+//! that is open can't be removed or renamed. The running transaction
+//! commits whenever the client goes quiet.
+
+use alloc::vec::Vec;
+
+use ipc::Channel;
+use libddk::BlockDevice;
+use libsys::{clock, Error, Result, Status};
+use vfs::path;
+use vfs::{node_kind, open_flags, DirEntry, FsInfo, NodeAttr, Vfs, VfsServer, MAX_DIR_BATCH, MAX_TRANSFER};
+
+use crate::layout::{kind, BLOCK_SIZE, ROOT_INODE};
+use crate::Volume;
+
+/// How long to sleep when no request is waiting (2ms)
+const POLL_INTERVAL: u64 = 2_000_000;
+
+struct Open {
+    inode: u32,
+    flags: u32,
+}
+
+/// A filesystem server for one volume
+pub struct RxfsServer<D: BlockDevice> {
+    volume: Volume<D>,
+
+    /// Open nodes, indexed by node ID
+    nodes: Vec<Option<Open>>,
+}
+
+impl<D: BlockDevice> RxfsServer<D> {
+    pub fn new(volume: Volume<D>) -> Self {
+        Self {
+            volume,
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn volume(&mut self) -> &mut Volume<D> {
+        &mut self.volume
+    }
+
+    /// Serve requests from `channel` until the client closes it, then
+    /// sync the volume
+    pub fn run(self, channel: &Channel) -> Result<()> {
+        let mut server = VfsServer(self);
+        let mut unsynced = false;
+        loop {
+            match vfs::protocol::poll(&mut server, channel) {
+                Ok(true) => unsynced = true,
+                Ok(false) if unsynced => {
+                    server.0.volume.sync()?;
+                    unsynced = false;
+                }
+                Ok(false) => rt::timer::sleep(POLL_INTERVAL),
+                Err(e) if e.status() == Status::PeerClosed => break,
+                Err(e) => return Err(e),
+            }
+        }
+        server.0.volume.sync()
+    }
+
+    /// Stamp the changes about to be made with the current time
+    fn touch(&mut self) {
+        if let Ok(now) = clock::utc() {
+            self.volume.set_time(now.max(0) as u64 / 1_000_000_000);
+        }
+    }
+
+    fn open_node(&mut self, id: u32) -> Result<&mut Open> {
+        self.nodes
+            .get_mut(id as usize)
+            .and_then(Option::as_mut)
+            .ok_or(Error::new(Status::InvalidArgs))
+    }
+
+    /// Inode of node `id`, if it was opened with all of `flags`
+    fn inode_for(&mut self, id: u32, flags: u32) -> Result<u32> {
+        let open = self.open_node(id)?;
+        if open.flags & flags != flags {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        Ok(open.inode)
+    }
+
+    /// Fail with `Busy` if inode `inode` is open
+    fn check_closed(&self, inode: u32) -> Result<()> {
+        match self.nodes.iter().flatten().any(|open| open.inode == inode) {
+            true => Err(Error::new(Status::Busy)),
+            false => Ok(()),
+        }
+    }
+
+    fn lookup_or_create(&mut self, path: &[u8], flags: u32) -> Result<u32> {
+        let parts = path::components(path)?;
+        let Some((&name, parents)) = parts.split_last() else {
+            return Ok(ROOT_INODE);
+        };
+        let dir = self.volume.resolve(parents)?;
+        match self.volume.lookup(dir, name) {
+            Ok(_)
+                if flags & (open_flags::CREATE | open_flags::EXCLUSIVE)
+                    == open_flags::CREATE | open_flags::EXCLUSIVE =>
+            {
+                Err(Error::new(Status::AlreadyExists))
+            }
+            Err(e) if e.status() == Status::NotFound && flags & open_flags::CREATE != 0 => {
+                if flags & open_flags::DIRECTORY != 0 {
+                    return Err(Error::new(Status::InvalidArgs));
+                }
+                self.touch();
+                self.volume.create(dir, name, kind::FILE)
+            }
+            result => result,
+        }
+    }
+}
+
+impl<D: BlockDevice> Vfs for RxfsServer<D> {
+    fn query(&mut self) -> Result<FsInfo> {
+        Ok(FsInfo {
+            name: b"rxfs".to_vec(),
+            label: self.volume.label().as_bytes().to_vec(),
+            block_size: BLOCK_SIZE as u32,
+            total_bytes: self.volume.total_bytes(),
+            free_bytes: self.volume.free_bytes(),
+        })
+    }
+
+    fn open(&mut self, path: Vec<u8>, flags: u32) -> Result<u32> {
+        let ino = self.lookup_or_create(&path, flags)?;
+        let inode = self.volume.inode(ino)?;
+        if inode.is_dir() && flags & (open_flags::WRITE | open_flags::TRUNCATE) != 0 {
+            return Err(Error::new(Status::WrongType));
+        }
+        if !inode.is_dir() && flags & open_flags::DIRECTORY != 0 {
+            return Err(Error::new(Status::WrongType));
+        }
+        if inode.read_only() && flags & open_flags::WRITE != 0 {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        if flags & open_flags::TRUNCATE != 0 {
+            if flags & open_flags::WRITE == 0 {
+                return Err(Error::new(Status::InvalidArgs));
+            }
+            if inode.size > 0 {
+                self.touch();
+                self.volume.truncate(ino, 0)?;
+            }
+        }
+
+        let open = Some(Open { inode: ino, flags });
+        match self.nodes.iter().position(Option::is_none) {
+            Some(id) => {
+                self.nodes[id] = open;
+                Ok(id as u32)
+            }
+            None => {
+                self.nodes.push(open);
+                Ok(self.nodes.len() as u32 - 1)
+            }
+        }
+    }
+
+    fn close(&mut self, node: u32) -> Result<()> {
+        self.open_node(node)?;
+        self.nodes[node as usize] = None;
+        Ok(())
+    }
+
+    fn stat(&mut self, node: u32) -> Result<NodeAttr> {
+        let ino = self.open_node(node)?.inode;
+        let inode = self.volume.inode(ino)?;
+        Ok(NodeAttr {
+            kind: if inode.is_dir() {
+                node_kind::DIRECTORY
+            } else {
+                node_kind::FILE
+            },
+            size: if inode.is_dir() { 0 } else { inode.size },
+            modified: inode.modified,
+            read_only: inode.read_only(),
+        })
+    }
+
+    fn read(&mut self, node: u32, offset: u64, len: u32) -> Result<Vec<u8>> {
+        if len > MAX_TRANSFER {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let ino = self.inode_for(node, open_flags::READ)?;
+        let mut data = alloc::vec![0u8; len as usize];
+        let read = self.volume.read(ino, offset, &mut data)?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    fn write(&mut self, node: u32, offset: u64, data: Vec<u8>) -> Result<u32> {
+        if data.len() > MAX_TRANSFER as usize {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let ino = self.inode_for(node, open_flags::WRITE)?;
+        self.touch();
+        Ok(self.volume.write(ino, offset, &data)? as u32)
+    }
+
+    fn truncate(&mut self, node: u32, size: u64) -> Result<()> {
+        let ino = self.inode_for(node, open_flags::WRITE)?;
+        self.touch();
+        self.volume.truncate(ino, size)
+    }
+
+    fn read_dir(&mut self, node: u32, cookie: u64) -> Result<Vec<DirEntry>> {
+        let ino = self.open_node(node)?.inode;
+        let children = self.volume.list(ino, cookie, MAX_DIR_BATCH as usize)?;
+        Ok(children
+            .into_iter()
+            .map(|child| DirEntry {
+                name: child.name.into_bytes(),
+                kind: if child.kind == kind::DIRECTORY {
+                    node_kind::DIRECTORY
+                } else {
+                    node_kind::FILE
+                },
+                size: child.size,
+                cookie: child.cookie,
+            })
+            .collect())
+    }
+
+    fn mkdir(&mut self, path: Vec<u8>) -> Result<()> {
+        let (parents, name) = path::split_last(&path)?;
+        let dir = self.volume.resolve(&parents)?;
+        self.touch();
+        self.volume.create(dir, name, kind::DIRECTORY)?;
+        Ok(())
+    }
+
+    fn unlink(&mut self, path: Vec<u8>) -> Result<()> {
+        let (parents, name) = path::split_last(&path)?;
+        let dir = self.volume.resolve(&parents)?;
+        let ino = self.volume.lookup(dir, name)?;
+        self.check_closed(ino)?;
+        self.touch();
+        self.volume.remove(dir, name)
+    }
+
+    fn rename(&mut self, from: Vec<u8>, to: Vec<u8>) -> Result<()> {
+        let (from_parents, from_name) = path::split_last(&from)?;
+        let (to_parents, to_name) = path::split_last(&to)?;
+        let from_dir = self.volume.resolve(&from_parents)?;
+        let to_dir = self.volume.resolve(&to_parents)?;
+        let ino = self.volume.lookup(from_dir, from_name)?;
+        self.check_closed(ino)?;
+        self.touch();
+        self.volume.rename(from_dir, from_name, to_dir, to_name)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.volume.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{self, RamDisk};
+    use open_flags::{CREATE, DIRECTORY, EXCLUSIVE, READ, TRUNCATE, WRITE};
+
+    fn server() -> RxfsServer<RamDisk> {
+        RxfsServer::new(image::mount())
+    }
+
+    fn names(server: &mut RxfsServer<RamDisk>, dir: u32) -> Vec<Vec<u8>> {
+        let mut names = Vec::new();
+        let mut cookie = 0;
+        loop {
+            let batch = server.read_dir(dir, cookie).unwrap();
+            let Some(last) = batch.last() else {
+                names.sort();
+                return names;
+            };
+            cookie = last.cookie;
+            names.extend(batch.into_iter().map(|entry| entry.name));
+        }
+    }
+
+    #[test]
+    fn test_files() {
+        let mut server = server();
+        let info = server.query().unwrap();
+        assert_eq!(info.name, b"rxfs");
+        assert_eq!(info.block_size, 4096);
+
+        server.mkdir(b"/boot".to_vec()).unwrap();
+        let file = server
+            .open(b"/boot/kernel.efi".to_vec(), READ | WRITE | CREATE)
+            .unwrap();
+        assert_eq!(server.write(file, 0, alloc::vec![0x4D; 30_000]).unwrap(), 30_000);
+        assert_eq!(server.read(file, 29_990, 100).unwrap(), [0x4D; 10]);
+        assert!(server.read(file, 0, MAX_TRANSFER + 1).is_err());
+
+        // A second open sees the first one's writes
+        let other = server.open(b"boot/./../boot/kernel.efi".to_vec(), READ).unwrap();
+        server.write(file, 30_000, b"tail".to_vec()).unwrap();
+        assert_eq!(server.stat(other).unwrap().size, 30_004);
+        assert_eq!(server.read(other, 30_000, 10).unwrap(), b"tail");
+        assert_eq!(
+            server.write(other, 0, b"x".to_vec()).unwrap_err().status(),
+            Status::AccessDenied
+        );
+
+        server.truncate(file, 10).unwrap();
+        assert_eq!(server.stat(other).unwrap().size, 10);
+        server.close(file).unwrap();
+        server.close(other).unwrap();
+        assert_eq!(server.close(other).unwrap_err().status(), Status::InvalidArgs);
+
+        let file = server.open(b"/boot/kernel.efi".to_vec(), WRITE | TRUNCATE).unwrap();
+        assert_eq!(server.stat(file).unwrap().size, 0);
+        assert_eq!(
+            server
+                .open(b"/boot/kernel.efi".to_vec(), CREATE | EXCLUSIVE)
+                .unwrap_err()
+                .status(),
+            Status::AlreadyExists
+        );
+        assert_eq!(
+            server.open(b"/boot".to_vec(), WRITE).unwrap_err().status(),
+            Status::WrongType
+        );
+        assert_eq!(
+            server
+                .open(b"/boot/kernel.efi".to_vec(), DIRECTORY)
+                .unwrap_err()
+                .status(),
+            Status::WrongType
+        );
+        assert_eq!(
+            server.open(b"/missing/file".to_vec(), CREATE).unwrap_err().status(),
+            Status::NotFound
+        );
+        assert_eq!(
+            server
+                .open(b"/boot/kernel.efi/x".to_vec(), CREATE)
+                .unwrap_err()
+                .status(),
+            Status::WrongType
+        );
+    }
+
+    #[test]
+    fn test_directories() {
+        let mut server = server();
+        server.mkdir(b"/docs".to_vec()).unwrap();
+        for index in 0..300 {
+            let path = alloc::format!("/docs/Chapter {}.txt", index);
+            let file = server.open(path.into_bytes(), CREATE | WRITE).unwrap();
+            server.close(file).unwrap();
+        }
+        let docs = server.open(b"/docs".to_vec(), READ | DIRECTORY).unwrap();
+        let listed = names(&mut server, docs);
+        assert_eq!(listed.len(), 300);
+        assert!(listed.contains(&b"Chapter 42.txt".to_vec()));
+
+        // Open nodes can't be removed or renamed, and full directories
+        // can't be removed
+        let file = server.open(b"/docs/Chapter 1.txt".to_vec(), READ).unwrap();
+        assert_eq!(
+            server.unlink(b"/docs/Chapter 1.txt".to_vec()).unwrap_err().status(),
+            Status::Busy
+        );
+        assert_eq!(
+            server
+                .rename(b"/docs/Chapter 1.txt".to_vec(), b"/one".to_vec())
+                .unwrap_err()
+                .status(),
+            Status::Busy
+        );
+        assert_eq!(server.unlink(b"/docs".to_vec()).unwrap_err().status(), Status::Busy);
+        server.close(docs).unwrap();
+        assert_eq!(server.unlink(b"/docs".to_vec()).unwrap_err().status(), Status::BadState);
+        server.close(file).unwrap();
+
+        server
+            .rename(b"/docs/Chapter 1.txt".to_vec(), b"/one".to_vec())
+            .unwrap();
+        server.unlink(b"/docs/Chapter 2.txt".to_vec()).unwrap();
+        let root = server.open(b"/".to_vec(), READ).unwrap();
+        assert_eq!(names(&mut server, root), [b"docs".to_vec(), b"one".to_vec()]);
+        assert_eq!(server.stat(root).unwrap().kind, node_kind::DIRECTORY);
+        server.sync().unwrap();
+        assert!(crate::check(server.volume()).unwrap().is_clean());
+    }
+}
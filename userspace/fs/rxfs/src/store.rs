@@ -0,0 +1,158 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Transactions
+//!
+//! Changes to metadata blocks collect in the running transaction, which
+//! also serves reads of the blocks it holds, until `commit` journals and
+//! checkpoints them. Operations nest inside it: `begin` starts one, and
+//! `end` either keeps its changes or, if it failed, puts back the blocks
+//! it changed, so a transaction only ever holds whole operations.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::disk::Disk;
+use crate::journal;
+use crate::layout::{Superblock, BLOCK_SIZE};
+
+/// A volume's blocks, with the running transaction
+pub struct Store<D: BlockDevice> {
+    disk: Disk<D>,
+    sb: Superblock,
+
+    /// Metadata blocks the running transaction changed
+    dirty: BTreeMap<u64, Box<[u8]>>,
+
+    /// What the running operation changed, with what the transaction held
+    /// before; `None` outside operations
+    undo: Option<BTreeMap<u64, Option<Box<[u8]>>>>,
+}
+
+impl<D: BlockDevice> Store<D> {
+    /// Open the volume on `disk`, replaying its journal
+    pub fn open(mut disk: Disk<D>) -> Result<Self> {
+        let mut block = vec![0u8; BLOCK_SIZE];
+        disk.read(0, &mut block)?;
+        let mut sb = Superblock::parse(&block)?;
+        if sb.block_count > disk.blocks() {
+            return Err(Error::new(Status::NotSupported));
+        }
+        journal::replay(&mut disk, &mut sb)?;
+        Ok(Self {
+            disk,
+            sb,
+            dirty: BTreeMap::new(),
+            undo: None,
+        })
+    }
+
+    pub fn superblock(&self) -> &Superblock {
+        &self.sb
+    }
+
+    pub fn disk_mut(&mut self) -> &mut Disk<D> {
+        &mut self.disk
+    }
+
+    pub fn into_disk(self) -> Disk<D> {
+        self.disk
+    }
+
+    /// Blocks in the running transaction
+    pub fn pending(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Metadata block `block`, as the running transaction has it
+    pub fn read(&mut self, block: u64) -> Result<Vec<u8>> {
+        if let Some(data) = self.dirty.get(&block) {
+            return Ok(data.to_vec());
+        }
+        let mut data = vec![0u8; BLOCK_SIZE];
+        self.disk.read(block, &mut data)?;
+        Ok(data)
+    }
+
+    /// Change metadata block `block` in the running transaction
+    pub fn update(&mut self, block: u64, change: impl FnOnce(&mut [u8])) -> Result<()> {
+        if !self.dirty.contains_key(&block) {
+            let mut data = vec![0u8; BLOCK_SIZE].into_boxed_slice();
+            self.disk.read(block, &mut data)?;
+            self.dirty.insert(block, data);
+            self.remember(block, None);
+        } else if self.undo.as_ref().is_some_and(|undo| !undo.contains_key(&block)) {
+            let previous = self.dirty[&block].clone();
+            self.remember(block, Some(previous));
+        }
+        change(self.dirty.get_mut(&block).unwrap());
+        Ok(())
+    }
+
+    /// Replace metadata block `block` in the running transaction
+    pub fn put(&mut self, block: u64, data: &[u8]) {
+        let previous = self.dirty.insert(block, data.into());
+        self.remember(block, previous);
+    }
+
+    /// Record what a block was before the running operation first
+    /// changed it
+    fn remember(&mut self, block: u64, previous: Option<Box<[u8]>>) {
+        if let Some(undo) = &mut self.undo {
+            undo.entry(block).or_insert(previous);
+        }
+    }
+
+    /// Read data blocks from `block` on; data isn't journaled
+    pub fn read_data(&mut self, block: u64, buf: &mut [u8]) -> Result<()> {
+        self.disk.read(block, buf)
+    }
+
+    /// Write data blocks from `block` on, straight to the disk
+    pub fn write_data(&mut self, block: u64, buf: &[u8]) -> Result<()> {
+        self.disk.write(block, buf)
+    }
+
+    /// Start an operation
+    pub fn begin(&mut self) {
+        self.undo = Some(BTreeMap::new());
+    }
+
+    /// End the running operation, undoing its changes unless it
+    /// succeeded
+    ///
+    /// Returns the blocks that were put back.
+    pub fn end(&mut self, succeeded: bool) -> Vec<u64> {
+        let undo = self.undo.take().unwrap_or_default();
+        if succeeded {
+            return Vec::new();
+        }
+        let blocks = undo.keys().copied().collect();
+        for (block, previous) in undo {
+            match previous {
+                Some(data) => self.dirty.insert(block, data),
+                None => self.dirty.remove(&block),
+            };
+        }
+        blocks
+    }
+
+    /// Journal and checkpoint the running transaction, then flush
+    pub fn commit(&mut self) -> Result<()> {
+        if !self.dirty.is_empty() {
+            let blocks: Vec<(u64, &[u8])> = self.dirty.iter().map(|(home, data)| (*home, &data[..])).collect();
+            journal::commit(&mut self.disk, &self.sb, &blocks)?;
+            journal::checkpoint(&mut self.disk, &mut self.sb, &blocks)?;
+            self.dirty.clear();
+        }
+        self.disk.flush()
+    }
+}
@@ -0,0 +1,769 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Volumes
+//!
+//! Nodes are named by inode number. Every change is one operation of the
+//! running transaction: if it fails part way, the blocks it changed and
+//! the bits it took are put back, so a failed call leaves the metadata as
+//! it was. The transaction commits on `sync`, or before an operation that
+//! might not fit in the journal with it.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::BlockDevice;
+use libsys::{Error, Result, Status};
+
+use crate::bitmap::Bitmap;
+use crate::dir::{self, MAX_BUCKETS};
+use crate::disk::Disk;
+use crate::journal;
+use crate::layout::{kind, Inode, Superblock, BLOCK_SIZE, ROOT_INODE};
+use crate::store::Store;
+
+/// Most metadata blocks one operation changes: a directory doubling to
+/// `MAX_BUCKETS`, plus inodes and bitmap blocks
+pub const MAX_OPERATION_BLOCKS: usize = MAX_BUCKETS as usize + 32;
+
+/// Largest file, in bytes
+pub const MAX_FILE_SIZE: u64 = u32::MAX as u64 * BLOCK_SIZE as u64;
+
+/// An entry of a directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Child {
+    pub name: String,
+    pub inode: u32,
+
+    /// One of `layout::kind`
+    pub kind: u16,
+
+    /// Bytes of a file; zero for directories
+    pub size: u64,
+
+    /// Where the listing goes on after this entry
+    pub cookie: u64,
+}
+
+/// Where an entry was found
+struct Found {
+    /// Block of the bucket
+    block: u64,
+
+    /// Byte offset of the entry in its bucket
+    offset: usize,
+
+    inode: u32,
+}
+
+/// A mounted volume
+pub struct Volume<D: BlockDevice> {
+    store: Store<D>,
+    inodes: Bitmap,
+    blocks: Bitmap,
+
+    /// Time stamped on changes, in seconds since the Unix epoch
+    now: u64,
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// Mount the volume on `device`, replaying its journal
+    ///
+    /// Fails with `NotSupported` if the device doesn't hold a volume.
+    pub fn mount(device: D) -> Result<Self> {
+        let mut store = Store::open(Disk::new(device)?)?;
+        let sb = *store.superblock();
+        let inodes = Bitmap::load(&mut store, sb.inode_bitmap_start, sb.inode_count as u64)?;
+        let blocks = Bitmap::load(&mut store, sb.block_bitmap_start, sb.block_count)?;
+        Ok(Self {
+            store,
+            inodes,
+            blocks,
+            now: 0,
+        })
+    }
+
+    pub fn superblock(&self) -> &Superblock {
+        self.store.superblock()
+    }
+
+    pub(crate) fn store_mut(&mut self) -> &mut Store<D> {
+        &mut self.store
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        self.store.disk_mut().device_mut()
+    }
+
+    /// Give the device back; unsynced changes are lost
+    pub fn into_device(self) -> D {
+        self.store.into_disk().into_device()
+    }
+
+    pub fn label(&self) -> &str {
+        self.superblock().label()
+    }
+
+    /// Bytes of the data area
+    pub fn total_bytes(&self) -> u64 {
+        let sb = self.superblock();
+        (sb.block_count - sb.data_start) * BLOCK_SIZE as u64
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.blocks.free() * BLOCK_SIZE as u64
+    }
+
+    /// Inodes not in use
+    pub fn free_inodes(&self) -> u64 {
+        self.inodes.free()
+    }
+
+    /// Set the time changes are stamped with, in seconds since the Unix
+    /// epoch
+    pub fn set_time(&mut self, now: u64) {
+        self.now = now;
+    }
+
+    /// Inode `ino`; fails with `NotFound` if it isn't in use
+    pub fn inode(&mut self, ino: u32) -> Result<Inode> {
+        if ino == 0 || ino >= self.superblock().inode_count {
+            return Err(Error::new(Status::NotFound));
+        }
+        let (block, offset) = self.superblock().inode_location(ino);
+        let inode = Inode::parse(&self.store.read(block)?[offset..]);
+        if inode.kind == kind::FREE {
+            return Err(Error::new(Status::NotFound));
+        }
+        Ok(inode)
+    }
+
+    fn put_inode(&mut self, ino: u32, inode: &Inode) -> Result<()> {
+        let (block, offset) = self.superblock().inode_location(ino);
+        self.store.update(block, |data| inode.store(&mut data[offset..]))
+    }
+
+    fn directory(&mut self, ino: u32) -> Result<Inode> {
+        let inode = self.inode(ino)?;
+        if !inode.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        Ok(inode)
+    }
+
+    fn file(&mut self, ino: u32) -> Result<Inode> {
+        let inode = self.inode(ino)?;
+        if inode.is_dir() {
+            return Err(Error::new(Status::WrongType));
+        }
+        Ok(inode)
+    }
+
+    /// Run `operation` in the running transaction, undoing what it did if
+    /// it fails
+    fn operation<T>(&mut self, operation: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let capacity = journal::capacity(self.superblock());
+        if self.store.pending() + MAX_OPERATION_BLOCKS > capacity {
+            self.sync()?;
+        }
+
+        self.store.begin();
+        self.inodes.begin();
+        self.blocks.begin();
+        let mut result = operation(self);
+        // Too big to ever commit
+        if result.is_ok() && self.store.pending() > capacity {
+            result = Err(Error::new(Status::NoMemory));
+        }
+        let blocks = self.store.end(result.is_ok());
+        if result.is_err() {
+            self.inodes.rollback(&mut self.store, &blocks)?;
+            self.blocks.rollback(&mut self.store, &blocks)?;
+        }
+        result
+    }
+
+    /// Map `count` unmapped logical blocks from `logical` to newly
+    /// allocated blocks, as few runs as the free space allows
+    fn allocate(&mut self, inode: &mut Inode, logical: u32, count: u32) -> Result<()> {
+        let mut done = 0;
+        while done < count {
+            let goal = (logical + done)
+                .checked_sub(1)
+                .and_then(|previous| inode.map(previous))
+                .map(|block| block + 1);
+            let (start, len) = self.blocks.allocate(&mut self.store, (count - done) as u64, goal)?;
+            inode.add_extent(logical + done, start as u32, len as u32)?;
+            done += len as u32;
+        }
+        Ok(())
+    }
+
+    /// Free the blocks of `inode` from logical block `first` on
+    fn free_from(&mut self, inode: &mut Inode, first: u32) -> Result<()> {
+        for (start, len) in inode.cut_extents(first) {
+            self.blocks.release(&mut self.store, start as u64, len as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Zero what follows byte `size` in the block holding it
+    fn zero_tail(&mut self, inode: &Inode, size: u64) -> Result<()> {
+        let at = (size % BLOCK_SIZE as u64) as usize;
+        let Some(block) = inode.map((size / BLOCK_SIZE as u64) as u32).filter(|_| at != 0) else {
+            return Ok(());
+        };
+        let mut data = vec![0u8; BLOCK_SIZE];
+        self.store.read_data(block, &mut data)?;
+        data[at..].fill(0);
+        self.store.write_data(block, &data)
+    }
+
+    fn bucket_block(dir: &Inode, index: u32) -> Result<u64> {
+        dir.map(index).ok_or(Error::new(Status::IoError))
+    }
+
+    fn bucket_count(dir: &Inode) -> u32 {
+        (dir.size / BLOCK_SIZE as u64) as u32
+    }
+
+    /// The entry called `name` in directory `dir`
+    fn find(&mut self, dir: &Inode, name: &[u8]) -> Result<Option<Found>> {
+        let count = Self::bucket_count(dir);
+        if count == 0 {
+            return Ok(None);
+        }
+        let first = dir::hash(name) % count;
+        for i in 0..count {
+            let block = Self::bucket_block(dir, (first + i) % count)?;
+            let bucket = self.store.read(block)?;
+            if let Some(entry) = dir::find(&bucket, name) {
+                return Ok(Some(Found {
+                    block,
+                    offset: entry.offset,
+                    inode: entry.inode,
+                }));
+            }
+            if !dir::overflowed(&bucket) {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Add an entry to directory `dir`, growing it if no bucket has room
+    fn insert(&mut self, dir: &mut Inode, name: &[u8], ino: u32, kind: u16) -> Result<()> {
+        loop {
+            let count = Self::bucket_count(dir);
+            let first = dir::hash(name) % count;
+            for i in 0..count {
+                let block = Self::bucket_block(dir, (first + i) % count)?;
+                if dir::has_room(&self.store.read(block)?, name) {
+                    let mut result = Ok(());
+                    self.store
+                        .update(block, |bucket| result = dir::insert(bucket, ino, kind, name))?;
+                    return result;
+                }
+                self.store.update(block, dir::set_overflowed)?;
+            }
+            self.grow(dir)?;
+        }
+    }
+
+    /// Double the buckets of directory `dir`, hashing every entry again
+    fn grow(&mut self, dir: &mut Inode) -> Result<()> {
+        let count = Self::bucket_count(dir);
+        let grown = count * 2;
+        if grown > MAX_BUCKETS {
+            return Err(Error::new(Status::NoMemory));
+        }
+
+        let mut buckets = vec![dir::empty(); grown as usize];
+        for index in 0..count {
+            let old = self.store.read(Self::bucket_block(dir, index)?)?;
+            for entry in dir::entries(&old) {
+                let first = dir::hash(entry.name) % grown;
+                let mut placed = false;
+                for i in 0..grown {
+                    let bucket = &mut buckets[((first + i) % grown) as usize];
+                    if dir::insert(bucket, entry.inode, entry.kind, entry.name).is_ok() {
+                        placed = true;
+                        break;
+                    }
+                    dir::set_overflowed(bucket);
+                }
+                if !placed {
+                    return Err(Error::new(Status::NoMemory));
+                }
+            }
+        }
+
+        self.free_from(dir, 0)?;
+        self.allocate(dir, 0, grown)?;
+        for (index, bucket) in buckets.iter().enumerate() {
+            self.store.put(Self::bucket_block(dir, index as u32)?, bucket);
+        }
+        dir.size = grown as u64 * BLOCK_SIZE as u64;
+        Ok(())
+    }
+
+    /// The node called `name` in directory `dir`
+    pub fn lookup(&mut self, dir: u32, name: &str) -> Result<u32> {
+        let dir = self.directory(dir)?;
+        let found = self.find(&dir, name.as_bytes())?;
+        found.map(|found| found.inode).ok_or(Error::new(Status::NotFound))
+    }
+
+    /// The node at the end of `path`, from the root
+    pub fn resolve(&mut self, path: &[&str]) -> Result<u32> {
+        let mut ino = ROOT_INODE;
+        for name in path {
+            ino = self.lookup(ino, name)?;
+        }
+        Ok(ino)
+    }
+
+    /// Up to `max` entries of directory `dir`, from `cookie` on (zero for
+    /// the first)
+    pub fn list(&mut self, dir: u32, cookie: u64, max: usize) -> Result<Vec<Child>> {
+        let dir = self.directory(dir)?;
+        let mut children = Vec::new();
+        let (mut index, mut offset) = ((cookie >> 13) as u32, (cookie & 0x1FFF) as usize);
+        while index < Self::bucket_count(&dir) && children.len() < max {
+            let bucket = self.store.read(Self::bucket_block(&dir, index)?)?;
+            for entry in dir::entries_from(&bucket, offset) {
+                if children.len() == max {
+                    break;
+                }
+                let size = match entry.kind {
+                    kind::FILE => self.inode(entry.inode)?.size,
+                    _ => 0,
+                };
+                children.push(Child {
+                    name: String::from_utf8_lossy(entry.name).into_owned(),
+                    inode: entry.inode,
+                    kind: entry.kind,
+                    size,
+                    cookie: (index as u64) << 13 | entry.end() as u64,
+                });
+            }
+            index += 1;
+            offset = 0;
+        }
+        Ok(children)
+    }
+
+    /// Read from file `ino` at `offset`, returning the bytes read
+    ///
+    /// Holes read as zeros.
+    pub fn read(&mut self, ino: u32, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let inode = self.file(ino)?;
+        if offset >= inode.size {
+            return Ok(0);
+        }
+        let len = buf.len().min((inode.size - offset) as usize);
+        let mut block = vec![0u8; BLOCK_SIZE];
+        let mut done = 0;
+        while done < len {
+            let position = offset + done as u64;
+            let at = (position % BLOCK_SIZE as u64) as usize;
+            let chunk = (BLOCK_SIZE - at).min(len - done);
+            match inode.map((position / BLOCK_SIZE as u64) as u32) {
+                Some(location) => {
+                    self.store.read_data(location, &mut block)?;
+                    buf[done..done + chunk].copy_from_slice(&block[at..at + chunk]);
+                }
+                None => buf[done..done + chunk].fill(0),
+            }
+            done += chunk;
+        }
+        Ok(len)
+    }
+
+    /// Write to file `ino` at `offset`, growing it as needed
+    ///
+    /// Writing past the end leaves a hole. Fails with `NoMemory` if the
+    /// volume is full or the file too fragmented, having written nothing.
+    pub fn write(&mut self, ino: u32, offset: u64, data: &[u8]) -> Result<usize> {
+        self.operation(|volume| volume.write_file(ino, offset, data))
+    }
+
+    fn write_file(&mut self, ino: u32, offset: u64, data: &[u8]) -> Result<usize> {
+        let mut inode = self.file(ino)?;
+        if inode.read_only() {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let end = offset
+            .checked_add(data.len() as u64)
+            .filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or(Error::new(Status::InvalidArgs))?;
+        let size = inode.size;
+        let first = (offset / BLOCK_SIZE as u64) as u32;
+        let last = ((end - 1) / BLOCK_SIZE as u64) as u32;
+
+        // Bytes past the old end read as zeros once the file grows
+        if offset > size && size / BLOCK_SIZE as u64 != first as u64 {
+            self.zero_tail(&inode, size)?;
+        }
+
+        // Fill the holes the write covers, all before any data is written
+        let mapped: Vec<bool> = (first..=last).map(|logical| inode.map(logical).is_some()).collect();
+        let mut logical = first;
+        while logical <= last {
+            if mapped[(logical - first) as usize] {
+                logical += 1;
+                continue;
+            }
+            let run = (logical..=last).take_while(|&l| !mapped[(l - first) as usize]).count() as u32;
+            self.allocate(&mut inode, logical, run)?;
+            logical += run;
+        }
+
+        let mut block = vec![0u8; BLOCK_SIZE];
+        for logical in first..=last {
+            let start = logical as u64 * BLOCK_SIZE as u64;
+            let from = (offset.max(start) - start) as usize;
+            let to = (end.min(start + BLOCK_SIZE as u64) - start) as usize;
+            let location = inode.map(logical).ok_or(Error::new(Status::IoError))?;
+            block.fill(0);
+            if mapped[(logical - first) as usize] && (from > 0 || to < BLOCK_SIZE) {
+                self.store.read_data(location, &mut block)?;
+                if size < start + BLOCK_SIZE as u64 {
+                    block[(size.max(start) - start) as usize..].fill(0);
+                }
+            }
+            let done = (start + from as u64 - offset) as usize;
+            block[from..to].copy_from_slice(&data[done..done + (to - from)]);
+            self.store.write_data(location, &block)?;
+        }
+
+        inode.size = size.max(end);
+        inode.modified = self.now;
+        self.put_inode(ino, &inode)?;
+        Ok(data.len())
+    }
+
+    /// Set the size of file `ino`, freeing blocks past the new end or
+    /// leaving a hole up to it
+    pub fn truncate(&mut self, ino: u32, size: u64) -> Result<()> {
+        self.operation(|volume| {
+            let mut inode = volume.file(ino)?;
+            if inode.read_only() {
+                return Err(Error::new(Status::AccessDenied));
+            }
+            if size > MAX_FILE_SIZE {
+                return Err(Error::new(Status::InvalidArgs));
+            }
+            if size < inode.size {
+                volume.free_from(&mut inode, size.div_ceil(BLOCK_SIZE as u64) as u32)?;
+            } else {
+                volume.zero_tail(&inode, inode.size)?;
+            }
+            inode.size = size;
+            inode.modified = volume.now;
+            volume.put_inode(ino, &inode)
+        })
+    }
+
+    /// Create a file or directory (`kind`) called `name` in directory
+    /// `dir`
+    ///
+    /// Fails with `AlreadyExists` if the name is taken, and with
+    /// `InvalidArgs` if it can't name a node.
+    pub fn create(&mut self, dir: u32, name: &str, kind: u16) -> Result<u32> {
+        if !dir::valid_name(name) || !matches!(kind, kind::FILE | kind::DIRECTORY) {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        self.operation(|volume| {
+            let parent = dir;
+            let mut dir = volume.directory(parent)?;
+            if volume.find(&dir, name.as_bytes())?.is_some() {
+                return Err(Error::new(Status::AlreadyExists));
+            }
+
+            let (ino, _) = volume.inodes.allocate(&mut volume.store, 1, None)?;
+            let ino = ino as u32;
+            let mut inode = Inode {
+                kind,
+                parent,
+                modified: volume.now,
+                ..Default::default()
+            };
+            if kind == kind::DIRECTORY {
+                volume.allocate(&mut inode, 0, 1)?;
+                volume.store.put(Self::bucket_block(&inode, 0)?, &dir::empty());
+                inode.size = BLOCK_SIZE as u64;
+            }
+            volume.put_inode(ino, &inode)?;
+
+            volume.insert(&mut dir, name.as_bytes(), ino, kind)?;
+            dir.entries += 1;
+            dir.modified = volume.now;
+            volume.put_inode(parent, &dir)?;
+            Ok(ino)
+        })
+    }
+
+    /// Remove the node called `name` from directory `dir`
+    ///
+    /// Fails with `BadState` if it is a directory that isn't empty, and
+    /// with `AccessDenied` if it is read-only.
+    pub fn remove(&mut self, dir: u32, name: &str) -> Result<()> {
+        self.operation(|volume| {
+            let parent = dir;
+            let mut dir = volume.directory(parent)?;
+            let found = volume
+                .find(&dir, name.as_bytes())?
+                .ok_or(Error::new(Status::NotFound))?;
+            let mut inode = volume.inode(found.inode)?;
+            if inode.read_only() {
+                return Err(Error::new(Status::AccessDenied));
+            }
+            if inode.is_dir() && inode.entries > 0 {
+                return Err(Error::new(Status::BadState));
+            }
+
+            volume
+                .store
+                .update(found.block, |bucket| dir::remove(bucket, found.offset))?;
+            volume.free_from(&mut inode, 0)?;
+            volume.put_inode(found.inode, &Inode::default())?;
+            volume.inodes.release(&mut volume.store, found.inode as u64, 1)?;
+
+            dir.entries -= 1;
+            dir.modified = volume.now;
+            volume.put_inode(parent, &dir)
+        })
+    }
+
+    /// Whether directory `ino` is `ancestor` or inside it
+    fn within(&mut self, mut ino: u32, ancestor: u32) -> Result<bool> {
+        for _ in 0..self.superblock().inode_count {
+            if ino == ancestor {
+                return Ok(true);
+            }
+            if ino == ROOT_INODE {
+                return Ok(false);
+            }
+            ino = self.inode(ino)?.parent;
+        }
+        Err(Error::new(Status::IoError))
+    }
+
+    /// Move the node called `from_name` in directory `from` to
+    /// `to_name` in directory `to`
+    ///
+    /// Fails with `AlreadyExists` if `to_name` is taken, and with
+    /// `InvalidArgs` if a directory would move into itself.
+    pub fn rename(&mut self, from: u32, from_name: &str, to: u32, to_name: &str) -> Result<()> {
+        if !dir::valid_name(to_name) {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        self.operation(|volume| {
+            let mut from_dir = volume.directory(from)?;
+            let found = volume
+                .find(&from_dir, from_name.as_bytes())?
+                .ok_or(Error::new(Status::NotFound))?;
+            let mut inode = volume.inode(found.inode)?;
+            if inode.read_only() {
+                return Err(Error::new(Status::AccessDenied));
+            }
+            if from == to && from_name == to_name {
+                return Ok(());
+            }
+            let to_dir = volume.directory(to)?;
+            if volume.find(&to_dir, to_name.as_bytes())?.is_some() {
+                return Err(Error::new(Status::AlreadyExists));
+            }
+            if inode.is_dir() && volume.within(to, found.inode)? {
+                return Err(Error::new(Status::InvalidArgs));
+            }
+
+            // Out first, so a rename within a directory has room
+            volume
+                .store
+                .update(found.block, |bucket| dir::remove(bucket, found.offset))?;
+            from_dir.modified = volume.now;
+            if from == to {
+                volume.insert(&mut from_dir, to_name.as_bytes(), found.inode, inode.kind)?;
+                volume.put_inode(from, &from_dir)?;
+            } else {
+                from_dir.entries -= 1;
+                volume.put_inode(from, &from_dir)?;
+                let mut to_dir = to_dir;
+                volume.insert(&mut to_dir, to_name.as_bytes(), found.inode, inode.kind)?;
+                to_dir.entries += 1;
+                to_dir.modified = volume.now;
+                volume.put_inode(to, &to_dir)?;
+            }
+
+            inode.parent = to;
+            volume.put_inode(found.inode, &inode)
+        })
+    }
+
+    /// Make node `ino` read-only, or writable again
+    pub fn set_read_only(&mut self, ino: u32, read_only: bool) -> Result<()> {
+        self.operation(|volume| {
+            let mut inode = volume.inode(ino)?;
+            inode.flags &= !crate::layout::flags::READ_ONLY;
+            if read_only {
+                inode.flags |= crate::layout::flags::READ_ONLY;
+            }
+            volume.put_inode(ino, &inode)
+        })
+    }
+
+    /// Commit the running transaction
+    pub fn sync(&mut self) -> Result<()> {
+        self.store.commit()?;
+        self.inodes.committed();
+        self.blocks.committed();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image;
+
+    #[test]
+    fn test_files() {
+        let mut volume = image::mount();
+        let free = volume.free_bytes();
+        let ino = volume.create(ROOT_INODE, "kernel.efi", kind::FILE).unwrap();
+        assert_eq!(volume.lookup(ROOT_INODE, "kernel.efi").unwrap(), ino);
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        assert_eq!(volume.write(ino, 0, &data).unwrap(), 10_000);
+        let mut buf = vec![0u8; 20_000];
+        assert_eq!(volume.read(ino, 0, &mut buf).unwrap(), 10_000);
+        assert_eq!(buf[..10_000], data[..]);
+        assert_eq!(volume.free_bytes(), free - 3 * BLOCK_SIZE as u64);
+
+        // A write past the end leaves a hole, and stale bytes past the old
+        // end don't show
+        volume.truncate(ino, 5000).unwrap();
+        volume.write(ino, 50_000, b"tail").unwrap();
+        assert_eq!(volume.inode(ino).unwrap().size, 50_004);
+        assert_eq!(volume.read(ino, 4990, &mut buf[..20]).unwrap(), 20);
+        assert_eq!(buf[..10], data[4990..5000]);
+        assert_eq!(buf[10..20], [0; 10]);
+        assert_eq!(volume.read(ino, 30_000, &mut buf[..4]).unwrap(), 4);
+        assert_eq!(buf[..4], [0; 4]);
+        assert_eq!(volume.read(ino, 50_000, &mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"tail");
+        assert_eq!(volume.free_bytes(), free - 3 * BLOCK_SIZE as u64);
+
+        volume.set_read_only(ino, true).unwrap();
+        assert_eq!(volume.write(ino, 0, b"x").unwrap_err().status(), Status::AccessDenied);
+        assert_eq!(
+            volume.remove(ROOT_INODE, "kernel.efi").unwrap_err().status(),
+            Status::AccessDenied
+        );
+        volume.set_read_only(ino, false).unwrap();
+        volume.remove(ROOT_INODE, "kernel.efi").unwrap();
+        assert_eq!(volume.inode(ino).unwrap_err().status(), Status::NotFound);
+
+        // Freed blocks come back once the transaction commits
+        volume.sync().unwrap();
+        assert_eq!(volume.free_bytes(), free);
+        assert!(crate::check(&mut volume).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_directories() {
+        let mut volume = image::mount();
+        let docs = volume.create(ROOT_INODE, "docs", kind::DIRECTORY).unwrap();
+        let names: Vec<String> = (0..500)
+            .map(|i| alloc::format!("A rather long file name, number {}.txt", i))
+            .collect();
+        for name in &names {
+            volume.create(docs, name, kind::FILE).unwrap();
+        }
+        assert!(volume.inode(docs).unwrap().size > BLOCK_SIZE as u64);
+        assert_eq!(volume.inode(docs).unwrap().entries, 500);
+        assert_eq!(
+            volume.create(docs, &names[7], kind::FILE).unwrap_err().status(),
+            Status::AlreadyExists
+        );
+        assert_eq!(
+            volume.create(docs, "a/b", kind::FILE).unwrap_err().status(),
+            Status::InvalidArgs
+        );
+
+        let mut listed = Vec::new();
+        let mut cookie = 0;
+        loop {
+            let batch = volume.list(docs, cookie, 64).unwrap();
+            let Some(last) = batch.last() else {
+                break;
+            };
+            cookie = last.cookie;
+            listed.extend(batch.into_iter().map(|child| child.name));
+        }
+        listed.sort();
+        let mut expected = names.clone();
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        // Moves, within a directory and across
+        assert_eq!(
+            volume.remove(ROOT_INODE, "docs").unwrap_err().status(),
+            Status::BadState
+        );
+        volume.rename(docs, &names[1], docs, "one").unwrap();
+        volume.rename(docs, "one", ROOT_INODE, "one").unwrap();
+        let one = volume.resolve(&["one"]).unwrap();
+        assert_eq!(volume.inode(one).unwrap().parent, ROOT_INODE);
+        assert_eq!(volume.lookup(docs, &names[1]).unwrap_err().status(), Status::NotFound);
+        assert_eq!(volume.inode(docs).unwrap().entries, 499);
+        let inner = volume.create(docs, "inner", kind::DIRECTORY).unwrap();
+        assert_eq!(
+            volume.rename(ROOT_INODE, "docs", inner, "docs").unwrap_err().status(),
+            Status::InvalidArgs
+        );
+        assert_eq!(
+            volume.rename(ROOT_INODE, "one", docs, "inner").unwrap_err().status(),
+            Status::AlreadyExists
+        );
+
+        for name in &names[2..] {
+            volume.remove(docs, name).unwrap();
+        }
+        volume.remove(docs, &names[0]).unwrap();
+        volume.remove(docs, "inner").unwrap();
+        volume.remove(ROOT_INODE, "docs").unwrap();
+        volume.sync().unwrap();
+        assert!(crate::check(&mut volume).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_failed_operations() {
+        let mut volume = image::mount();
+        let ino = volume.create(ROOT_INODE, "big", kind::FILE).unwrap();
+        volume.sync().unwrap();
+        let free = volume.free_bytes();
+
+        // Filling the volume fails with nothing written
+        let data = vec![0x5A; (free + BLOCK_SIZE as u64) as usize];
+        assert_eq!(volume.write(ino, 0, &data).unwrap_err().status(), Status::NoMemory);
+        assert_eq!(volume.free_bytes(), free);
+        assert_eq!(volume.inode(ino).unwrap().size, 0);
+
+        // Fragments past what an inode maps
+        let status = (0..40u64).find_map(|index| volume.write(ino, index * 2 * BLOCK_SIZE as u64, b"x").err());
+        assert_eq!(status.map(|e| e.status()), Some(Status::NoMemory));
+        assert_eq!(volume.inode(ino).unwrap().extents.len(), crate::layout::MAX_EXTENTS);
+        volume.sync().unwrap();
+        assert!(crate::check(&mut volume).unwrap().is_clean());
+    }
+}
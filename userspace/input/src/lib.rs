@@ -0,0 +1,50 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Input Service
+//!
+//! Collects key events from every keyboard the system has and hands them
+//! to its subscribers over channels:
+//! - PS/2 keyboards on the i8042
+//! - USB keyboards, through the xHCI driver
+//! - The serial console, whose terminal input is decoded back into keys
+//!
+//! Events are DDK `KeyEvent`s whatever their source, so consumers see
+//! HID usages, press and release, and the modifier state. See `protocol`
+//! for the channel messages.
+//!
+//! There is no way yet to hand a channel to another process, so the
+//! service runs on a thread of its consumer, the way the console runs
+//! its shell; the channels keep the two sides apart for when it becomes
+//! a process of its own.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! fn keys(root: &libsys::Handle, log: &mut dyn core::fmt::Write) -> libsys::Result<()> {
+//!     let mut service = input::InputService::start(root, log);
+//!     let events = service.subscribe()?;
+//!     service.poll();
+//!     for event in input::protocol::read(&events)? {
+//!         let _ = event.code;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod protocol;
+pub mod serial;
+pub mod service;
+pub mod usb;
+
+// Re-export commonly used types
+pub use serial::SerialInput;
+pub use service::InputService;
+pub use usb::UsbKeyboards;
@@ -0,0 +1,75 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Event Channel Protocol
+//!
+//! The service writes events to each subscriber channel in messages of
+//! up to `MAX_BATCH` `KeyEvent` records, back to back in their
+//! `KeyEvent::to_bytes` encoding. Messages carry no handles. The service
+//! never blocks on a subscriber: one that falls too far behind loses
+//! events, and one whose end is closed is dropped.
+
+use alloc::vec::Vec;
+
+use libddk::KeyEvent;
+use libipc::Channel;
+use libsys::{Result, Status};
+
+/// Most events in one message
+pub const MAX_BATCH: usize = 64;
+
+/// Largest message
+pub const MAX_MESSAGE: usize = MAX_BATCH * KeyEvent::SIZE;
+
+/// Encode up to `MAX_BATCH` events as a message
+pub fn encode(events: &[KeyEvent]) -> Vec<u8> {
+    events.iter().take(MAX_BATCH).flat_map(|event| event.to_bytes()).collect()
+}
+
+/// Decode a message, ignoring a trailing partial record
+pub fn decode(message: &[u8]) -> impl Iterator<Item = KeyEvent> + '_ {
+    message.chunks_exact(KeyEvent::SIZE).filter_map(KeyEvent::from_bytes)
+}
+
+/// Read the events of every message waiting on a subscriber channel
+///
+/// Returns no events when nothing is waiting.
+pub fn read(channel: &Channel) -> Result<Vec<KeyEvent>> {
+    let mut events = Vec::new();
+    let mut message = [0u8; MAX_MESSAGE];
+    let mut handles = Vec::new();
+    loop {
+        match channel.read(&mut message, &mut handles) {
+            Ok(len) if len > 0 => events.extend(decode(&message[..len])),
+            // A closed service is reported once its last events are read
+            Err(e) if e.status() == Status::PeerClosed && events.is_empty() => return Err(e),
+            // Empty
+            _ => return Ok(events),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libddk::input::{key, modifiers};
+
+    #[test]
+    fn test_encode_decode() {
+        let events = [
+            KeyEvent::new(key::LEFT_SHIFT, true, modifiers::LEFT_SHIFT),
+            KeyEvent::new(key::A, true, modifiers::LEFT_SHIFT),
+            KeyEvent::new(key::A, false, modifiers::LEFT_SHIFT),
+        ];
+        let message = encode(&events);
+        assert_eq!(message.len(), 3 * KeyEvent::SIZE);
+        assert!(decode(&message).eq(events));
+        assert_eq!(decode(&message[..KeyEvent::SIZE + 3]).count(), 1);
+
+        let many = [KeyEvent::new(key::SPACE, true, 0); MAX_BATCH + 1];
+        assert_eq!(encode(&many).len(), MAX_MESSAGE);
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Serial Console Input
+//!
+//! Reads what is typed on the kernel's debug console (`rx_debug_read`),
+//! normally a serial terminal, and decodes it back into key events. An
+//! escape sequence is held until the rest arrives; one still incomplete
+//! when the console goes quiet is taken as the keys it started with,
+//! such as a lone Esc.
+
+use alloc::collections::VecDeque;
+
+use libddk::input::{input_kind, TerminalDecoder};
+use libddk::{InputDevice, InputInfo, KeyEvent};
+use libsys::{debug, Handle, Result, Rights};
+
+/// Bytes read from the console at once
+const READ_CHUNK: usize = 64;
+
+/// Key events from the debug console
+pub struct SerialInput {
+    root: Handle,
+    decoder: TerminalDecoder,
+
+    /// Decoded events not yet read
+    events: VecDeque<KeyEvent>,
+}
+
+impl SerialInput {
+    /// Read the debug console through the root resource `root`
+    pub fn new(root: &Handle) -> Result<Self> {
+        Ok(Self {
+            root: root.duplicate(Rights::SAME_RIGHTS)?,
+            decoder: TerminalDecoder::new(),
+            events: VecDeque::new(),
+        })
+    }
+}
+
+impl InputDevice for SerialInput {
+    fn info(&self) -> InputInfo {
+        InputInfo {
+            kind: input_kind::KEYBOARD,
+            vendor_id: 0,
+            product_id: 0,
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<KeyEvent>> {
+        if self.events.is_empty() {
+            let mut buf = [0u8; READ_CHUNK];
+            let n = debug::read(&self.root, &mut buf)?;
+            let events = &mut self.events;
+            for &byte in &buf[..n] {
+                self.decoder.feed(byte, |event| events.push_back(event));
+            }
+            if n == 0 {
+                self.decoder.flush(|event| events.push_back(event));
+            }
+        }
+        Ok(self.events.pop_front())
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Input Event Service
+//!
+//! Polls every source in turn and writes what they produced to all
+//! subscribers, so events from different keyboards interleave in the
+//! order they were picked up. A source that fails is dropped; the others
+//! carry on.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use libddk::{InputDevice, KeyEvent};
+use libipc::Channel;
+use libsys::{Handle, Result, Status};
+use ps2::I8042;
+
+use crate::protocol;
+use crate::serial::SerialInput;
+use crate::usb::UsbKeyboards;
+
+/// How long to sleep when no source had anything
+const POLL_INTERVAL: u64 = 5_000_000;
+
+/// Routes key events from all keyboards to subscribers
+pub struct InputService {
+    /// Keyboards read through `InputDevice`: PS/2 and serial
+    devices: Vec<Box<dyn InputDevice>>,
+
+    usb: UsbKeyboards,
+
+    /// Service ends of the subscriber channels
+    subscribers: Vec<Channel>,
+}
+
+impl InputService {
+    /// Find the keyboards, logging what was found to `log`
+    pub fn start(root: &Handle, log: &mut dyn Write) -> Self {
+        let mut devices: Vec<Box<dyn InputDevice>> = Vec::new();
+        match I8042::init(root) {
+            Ok(i8042) => {
+                let _ = writeln!(log, "input: ps/2 keyboard");
+                devices.push(Box::new(i8042));
+            }
+            Err(e) if e.status() == Status::NotFound || e.status() == Status::NotSupported => {}
+            Err(e) => {
+                let _ = writeln!(log, "input: i8042 setup failed: {:?}", e);
+            }
+        }
+        match SerialInput::new(root) {
+            Ok(serial) => devices.push(Box::new(serial)),
+            Err(e) => {
+                let _ = writeln!(log, "input: serial setup failed: {:?}", e);
+            }
+        }
+
+        Self {
+            devices,
+            usb: UsbKeyboards::start(root, log),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Add a subscriber, returning its end of the event channel
+    pub fn subscribe(&mut self) -> Result<Channel> {
+        let (service_end, client_end) = Channel::create()?;
+        self.subscribers.push(service_end);
+        Ok(client_end)
+    }
+
+    /// Number of subscribers still listening
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// Move events from the keyboards to the subscribers
+    ///
+    /// Returns true if any work was done.
+    pub fn poll(&mut self) -> bool {
+        let mut events: Vec<KeyEvent> = Vec::new();
+        self.devices.retain_mut(|device| loop {
+            match device.read_event() {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => return true,
+                Err(_) => return false,
+            }
+        });
+        let usb_busy = self.usb.poll(|event| events.push(event));
+        if events.is_empty() {
+            return usb_busy;
+        }
+
+        for batch in events.chunks(protocol::MAX_BATCH) {
+            let message = protocol::encode(batch);
+            // A full channel only costs that subscriber these events
+            self.subscribers
+                .retain(|channel| !matches!(channel.write(&message, &[]), Err(e) if e.status() == Status::PeerClosed));
+        }
+        true
+    }
+
+    /// Serve until every subscriber has gone
+    pub fn run(&mut self) {
+        while !self.subscribers.is_empty() {
+            if !self.poll() {
+                rt::timer::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! USB Keyboards
+//!
+//! The service drives the xHCI controllers itself, each through a driver
+//! host of its own. Keyboards are opened as the controllers publish
+//! them, including ones plugged in later.
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use libddk::host::find_driver;
+use libddk::{DriverEntry, InputClient, InputDevice, KeyEvent, LocalDevice, PciDevice};
+use libsys::Handle;
+
+/// Drivers the service hosts
+static DRIVERS: [DriverEntry; 1] = [usb::xhci::DRIVER];
+
+/// A bound controller and the keyboards opened on it
+struct Controller {
+    local: LocalDevice,
+
+    /// Next input instance to try; keyboards are numbered from 1
+    next_instance: u32,
+
+    keyboards: Vec<InputClient>,
+}
+
+/// USB keyboards on all controllers
+pub struct UsbKeyboards {
+    controllers: Vec<Controller>,
+}
+
+impl UsbKeyboards {
+    /// Bind every xHCI controller
+    pub fn start(root: &Handle, log: &mut dyn Write) -> Self {
+        let mut controllers = Vec::new();
+        let mut index = 0;
+        while let Ok(device) = PciDevice::get_nth(root, index) {
+            index += 1;
+            let info = *device.info();
+            let desc = info.descriptor();
+            if find_driver(&DRIVERS, &desc).is_none() {
+                continue;
+            }
+
+            match LocalDevice::bind(&DRIVERS, desc, device.into_handle()) {
+                Ok(local) => {
+                    let _ = writeln!(
+                        log,
+                        "input: xhci at {:02x}:{:02x}.{}",
+                        info.bus_id, info.dev_id, info.func_id
+                    );
+                    controllers.push(Controller {
+                        local,
+                        next_instance: 1,
+                        keyboards: Vec::new(),
+                    });
+                }
+                Err(e) => {
+                    let _ = writeln!(log, "input: xhci bind failed: {:?}", e);
+                }
+            }
+        }
+        Self { controllers }
+    }
+
+    /// Service the controllers and pass key events to `emit`
+    ///
+    /// Returns true if any work was done.
+    pub fn poll(&mut self, mut emit: impl FnMut(KeyEvent)) -> bool {
+        let mut busy = false;
+        // A controller that fails is dropped along with its keyboards
+        self.controllers.retain_mut(|controller| {
+            match controller.local.host_mut().poll() {
+                Ok(worked) => busy |= worked,
+                Err(_) => return false,
+            }
+
+            if let Ok((body, handles)) = controller.local.open(controller.next_instance) {
+                if let Ok(client) = InputClient::new(&body, handles) {
+                    controller.keyboards.push(client);
+                }
+                controller.next_instance += 1;
+            }
+
+            for keyboard in controller.keyboards.iter_mut() {
+                while let Ok(Some(event)) = keyboard.read_event() {
+                    emit(event);
+                    busy = true;
+                }
+            }
+            true
+        });
+        busy
+    }
+}
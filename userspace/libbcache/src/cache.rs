@@ -0,0 +1,663 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Block Cache
+//!
+//! `BlockCache` is itself a `BlockDevice`, so a filesystem mounts it in
+//! place of the device it wraps. The device is cached in pages of
+//! `page_size` bytes kept in one mapped VMO:
+//! - Reads are served from the cache, loading missing pages in runs, and
+//!   sequential streams are read ahead
+//! - Writes only change the cache; dirty pages go to the device when
+//!   evicted, when too many are dirty, and on `flush`
+//! - `flush` writes every dirty page back and then flushes the device, so
+//!   it is the same barrier for the filesystem as the device's own flush
+//!
+//! Blocks past the last whole page aren't cached.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libddk::block::check_range;
+use libddk::{BlockDevice, BlockInfo, DeviceHealth, IoBuffer};
+use libsys::{Error, Result, Status};
+
+use crate::lru::Lru;
+use crate::readahead::ReadAhead;
+
+/// Memory the cache keeps its pages in
+pub trait Backing {
+    fn bytes(&self) -> &[u8];
+    fn bytes_mut(&mut self) -> &mut [u8];
+}
+
+impl Backing for IoBuffer {
+    fn bytes(&self) -> &[u8] {
+        self.slice(0, self.size()).unwrap_or_default()
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        let size = self.size();
+        self.slice_mut(0, size).unwrap_or_default()
+    }
+}
+
+impl Backing for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// How a cache is sized and behaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Bytes in a page; a multiple of the device's block size
+    pub page_size: usize,
+
+    /// Pages the cache holds
+    pub pages: usize,
+
+    /// Most pages kept cached ahead of a sequential reader; zero turns
+    /// read-ahead off
+    pub max_read_ahead: usize,
+
+    /// Dirty pages allowed before they are all written back
+    pub dirty_limit: usize,
+}
+
+impl Default for CacheConfig {
+    /// 4 MiB of 4 KiB pages, reading up to 128 KiB ahead
+    fn default() -> Self {
+        Self {
+            page_size: 4096,
+            pages: 1024,
+            max_read_ahead: 32,
+            dirty_limit: 512,
+        }
+    }
+}
+
+/// What the cache has done since it was created
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Pages read or partly written that were cached
+    pub hits: u64,
+
+    /// Pages read or partly written that had to be loaded
+    pub misses: u64,
+
+    /// Pages loaded ahead of a sequential reader
+    pub read_ahead: u64,
+
+    /// Pages loaded ahead that were read before being evicted
+    pub read_ahead_hits: u64,
+
+    /// Pages evicted to make room
+    pub evictions: u64,
+
+    /// Dirty pages written back to the device
+    pub write_backs: u64,
+
+    /// Flushes passed on to the device
+    pub flushes: u64,
+}
+
+impl Stats {
+    /// Hits as a share of lookups, in percent
+    pub fn hit_rate(&self) -> u64 {
+        match self.hits + self.misses {
+            0 => 0,
+            lookups => self.hits * 100 / lookups,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    page: u64,
+    dirty: bool,
+
+    /// Loaded ahead and not read yet
+    prefetched: bool,
+}
+
+/// A write-back cache in front of a block device
+pub struct BlockCache<D: BlockDevice, B: Backing = IoBuffer> {
+    device: D,
+    info: BlockInfo,
+    config: CacheConfig,
+    backing: B,
+
+    /// Device blocks in a page
+    page_blocks: u64,
+
+    /// Pages wholly on the device
+    page_count: u64,
+
+    slots: Vec<Slot>,
+
+    /// Slot of each cached page
+    map: BTreeMap<u64, usize>,
+    lru: Lru,
+    free: Vec<usize>,
+    dirty: usize,
+
+    read_ahead: ReadAhead,
+    stats: Stats,
+}
+
+impl<D: BlockDevice> BlockCache<D> {
+    /// Cache `device` in a new VMO
+    pub fn new(device: D, config: CacheConfig) -> Result<Self> {
+        let size = config
+            .page_size
+            .checked_mul(config.pages)
+            .ok_or(Error::new(Status::InvalidArgs))?;
+        let backing = IoBuffer::create(size, "block-cache")?;
+        Self::with_backing(device, config, backing)
+    }
+}
+
+impl<D: BlockDevice, B: Backing> BlockCache<D, B> {
+    /// Cache `device` in `backing`, which must hold `config.pages` pages
+    ///
+    /// Fails with `InvalidArgs` if the page size isn't a multiple of the
+    /// device's block size.
+    pub fn with_backing(device: D, config: CacheConfig, backing: B) -> Result<Self> {
+        let info = device.info();
+        let block_size = info.block_size as usize;
+        if block_size == 0
+            || config.page_size == 0
+            || !config.page_size.is_multiple_of(block_size)
+            || config.pages == 0
+            || backing.bytes().len() < config.page_size * config.pages
+        {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let page_blocks = (config.page_size / block_size) as u64;
+        Ok(Self {
+            device,
+            info,
+            config,
+            backing,
+            page_blocks,
+            page_count: info.block_count / page_blocks,
+            slots: vec![Slot::default(); config.pages],
+            map: BTreeMap::new(),
+            lru: Lru::new(config.pages),
+            free: (0..config.pages).rev().collect(),
+            dirty: 0,
+            read_ahead: ReadAhead::new(config.max_read_ahead as u64),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Pages waiting to be written back
+    pub fn dirty_pages(&self) -> usize {
+        self.dirty
+    }
+
+    /// Write everything back and give the device back
+    pub fn into_inner(mut self) -> Result<D> {
+        self.flush()?;
+        Ok(self.device)
+    }
+
+    fn data(&self, slot: usize) -> &[u8] {
+        let size = self.config.page_size;
+        &self.backing.bytes()[slot * size..(slot + 1) * size]
+    }
+
+    fn data_mut(&mut self, slot: usize) -> &mut [u8] {
+        let size = self.config.page_size;
+        &mut self.backing.bytes_mut()[slot * size..(slot + 1) * size]
+    }
+
+    /// Bytes the device takes in one transfer, a whole number of blocks
+    fn transfer_size(&self) -> usize {
+        let block_size = self.info.block_size as usize;
+        match self.info.max_transfer as usize {
+            0 => usize::MAX,
+            max => (max - max % block_size).max(block_size),
+        }
+    }
+
+    fn device_read(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let (chunk, block_size) = (self.transfer_size(), self.info.block_size as usize);
+        for (i, part) in buf.chunks_mut(chunk).enumerate() {
+            self.device.read_blocks(lba + (i * chunk / block_size) as u64, part)?;
+        }
+        Ok(())
+    }
+
+    fn device_write(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        let (chunk, block_size) = (self.transfer_size(), self.info.block_size as usize);
+        for (i, part) in buf.chunks(chunk).enumerate() {
+            self.device.write_blocks(lba + (i * chunk / block_size) as u64, part)?;
+        }
+        Ok(())
+    }
+
+    /// A slot to load a page into, evicting the least recently used page
+    /// if none is free
+    fn take_slot(&mut self) -> Result<usize> {
+        if let Some(slot) = self.free.pop() {
+            return Ok(slot);
+        }
+        let slot = self.lru.oldest().ok_or(Error::new(Status::NoMemory))?;
+        if self.slots[slot].dirty {
+            self.write_back(&[slot])?;
+        }
+        self.map.remove(&self.slots[slot].page);
+        self.lru.remove(slot);
+        self.stats.evictions += 1;
+        Ok(slot)
+    }
+
+    fn insert(&mut self, page: u64, slot: usize, prefetched: bool) {
+        self.slots[slot] = Slot {
+            page,
+            dirty: false,
+            prefetched,
+        };
+        self.map.insert(page, slot);
+        self.lru.touch(slot);
+    }
+
+    /// Load `count` pages from `first`; the first `demand` of them were
+    /// asked for, the rest are read ahead
+    fn load(&mut self, first: u64, count: u64, demand: u64) -> Result<()> {
+        let count = count.min(self.slots.len() as u64);
+        let size = self.config.page_size;
+        let mut data = vec![0u8; count as usize * size];
+        self.device_read(first * self.page_blocks, &mut data)?;
+        for (i, page) in data.chunks_exact(size).enumerate() {
+            let slot = self.take_slot()?;
+            self.data_mut(slot).copy_from_slice(page);
+            self.insert(first + i as u64, slot, i as u64 >= demand);
+        }
+        self.stats.misses += demand.min(count);
+        self.stats.read_ahead += count.saturating_sub(demand);
+        Ok(())
+    }
+
+    /// Write dirty slots back, in runs of consecutive pages
+    ///
+    /// `slots` must be in page order.
+    fn write_back(&mut self, slots: &[usize]) -> Result<()> {
+        let size = self.config.page_size;
+        let mut start = 0;
+        while start < slots.len() {
+            let first = self.slots[slots[start]].page;
+            let mut end = start + 1;
+            while end < slots.len() && self.slots[slots[end]].page == first + (end - start) as u64 {
+                end += 1;
+            }
+            let mut data = Vec::with_capacity((end - start) * size);
+            for &slot in &slots[start..end] {
+                data.extend_from_slice(self.data(slot));
+            }
+            self.device_write(first * self.page_blocks, &data)?;
+            for &slot in &slots[start..end] {
+                self.slots[slot].dirty = false;
+            }
+            self.dirty -= end - start;
+            self.stats.write_backs += (end - start) as u64;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Write every dirty page back
+    fn write_back_all(&mut self) -> Result<()> {
+        // The map is in page order
+        let dirty: Vec<usize> = self
+            .map
+            .values()
+            .copied()
+            .filter(|&slot| self.slots[slot].dirty)
+            .collect();
+        self.write_back(&dirty)
+    }
+
+    /// Keep the `window` pages after `end` cached, loading them once
+    /// fewer than half are
+    fn read_ahead(&mut self, end: u64, window: u64) -> Result<()> {
+        let window = window.min(self.page_count.saturating_sub(end));
+        let cached = (end..end + window)
+            .take_while(|page| self.map.contains_key(page))
+            .count() as u64;
+        if cached >= window.div_ceil(2) {
+            return Ok(());
+        }
+        let first = end + cached;
+        let count = (first..end + window)
+            .take_while(|page| !self.map.contains_key(page))
+            .count() as u64;
+        self.load(first, count, 0)
+    }
+
+    /// Byte range of `page` within a request for bytes `start..end` of
+    /// the device, as offsets into the page and into the request
+    fn overlap(&self, page: u64, start: u64, end: u64) -> (usize, usize, usize) {
+        let page_start = page * self.config.page_size as u64;
+        let from = start.max(page_start);
+        let to = end.min(page_start + self.config.page_size as u64);
+        (
+            (from - page_start) as usize,
+            (to - page_start) as usize,
+            (from - start) as usize,
+        )
+    }
+}
+
+impl<D: BlockDevice, B: Backing> BlockDevice for BlockCache<D, B> {
+    fn info(&self) -> BlockInfo {
+        self.info
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let blocks = check_range(&self.info, lba, buf.len())?;
+        let block_size = self.info.block_size as u64;
+        let cached_end = (self.page_count * self.page_blocks).clamp(lba, lba + blocks);
+        let split = ((cached_end - lba) * block_size) as usize;
+        if cached_end < lba + blocks {
+            self.device_read(cached_end, &mut buf[split..])?;
+        }
+        if split == 0 {
+            return Ok(());
+        }
+
+        let (start, end) = (lba * block_size, cached_end * block_size);
+        let first = lba / self.page_blocks;
+        let last = (cached_end - 1) / self.page_blocks;
+        let window = self.read_ahead.access(first, last + 1);
+        let mut loaded = first;
+        for page in first..=last {
+            let slot = match self.map.get(&page) {
+                Some(&slot) => {
+                    if page >= loaded {
+                        self.stats.hits += 1;
+                        if self.slots[slot].prefetched {
+                            self.stats.read_ahead_hits += 1;
+                        }
+                    }
+                    slot
+                }
+                None => {
+                    let run = (page..=last).take_while(|page| !self.map.contains_key(page)).count() as u64;
+                    self.load(page, run, run)?;
+                    loaded = page + run;
+                    self.map[&page]
+                }
+            };
+            self.slots[slot].prefetched = false;
+            self.lru.touch(slot);
+            let (from, to, at) = self.overlap(page, start, end);
+            buf[at..at + to - from].copy_from_slice(&self.data(slot)[from..to]);
+        }
+        if window > 0 {
+            self.read_ahead(last + 1, window)?;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        let blocks = check_range(&self.info, lba, buf.len())?;
+        let block_size = self.info.block_size as u64;
+        let cached_end = (self.page_count * self.page_blocks).clamp(lba, lba + blocks);
+        let split = ((cached_end - lba) * block_size) as usize;
+        if cached_end < lba + blocks {
+            self.device_write(cached_end, &buf[split..])?;
+        }
+        if split == 0 {
+            return Ok(());
+        }
+
+        let (start, end) = (lba * block_size, cached_end * block_size);
+        for page in lba / self.page_blocks..=(cached_end - 1) / self.page_blocks {
+            let (from, to, at) = self.overlap(page, start, end);
+            let whole = from == 0 && to == self.config.page_size;
+            let slot = match self.map.get(&page) {
+                Some(&slot) => {
+                    if !whole {
+                        self.stats.hits += 1;
+                    }
+                    slot
+                }
+                None if whole => {
+                    let slot = self.take_slot()?;
+                    self.insert(page, slot, false);
+                    slot
+                }
+                None => {
+                    self.load(page, 1, 1)?;
+                    self.map[&page]
+                }
+            };
+            self.data_mut(slot)[from..to].copy_from_slice(&buf[at..at + to - from]);
+            if !self.slots[slot].dirty {
+                self.slots[slot].dirty = true;
+                self.dirty += 1;
+            }
+            self.slots[slot].prefetched = false;
+            self.lru.touch(slot);
+        }
+        if self.dirty > self.config.dirty_limit {
+            self.write_back_all()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_back_all()?;
+        self.stats.flushes += 1;
+        self.device.flush()
+    }
+
+    fn health(&mut self) -> Result<DeviceHealth> {
+        self.device.health()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the disk was asked to do
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Op {
+        Read(u64, usize),
+        Write(u64, usize),
+        Flush,
+    }
+
+    /// A disk in memory with 512-byte blocks that logs its requests
+    struct LogDisk {
+        data: Vec<u8>,
+        max_transfer: u32,
+        log: Vec<Op>,
+    }
+
+    impl LogDisk {
+        fn new(blocks: usize) -> Self {
+            let data = (0..blocks * 512).map(|i| (i / 512) as u8).collect();
+            Self {
+                data,
+                max_transfer: 0,
+                log: Vec::new(),
+            }
+        }
+    }
+
+    impl BlockDevice for LogDisk {
+        fn info(&self) -> BlockInfo {
+            BlockInfo {
+                block_size: 512,
+                max_transfer: self.max_transfer,
+                block_count: (self.data.len() / 512) as u64,
+            }
+        }
+
+        fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+            let start = lba as usize * 512;
+            buf.copy_from_slice(&self.data[start..start + buf.len()]);
+            self.log.push(Op::Read(lba, buf.len() / 512));
+            Ok(())
+        }
+
+        fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+            let start = lba as usize * 512;
+            self.data[start..start + buf.len()].copy_from_slice(buf);
+            self.log.push(Op::Write(lba, buf.len() / 512));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.log.push(Op::Flush);
+            Ok(())
+        }
+    }
+
+    fn open(disk: LogDisk, pages: usize, max_read_ahead: usize) -> BlockCache<LogDisk, Vec<u8>> {
+        let config = CacheConfig {
+            page_size: 4096,
+            pages,
+            max_read_ahead,
+            dirty_limit: pages,
+        };
+        BlockCache::with_backing(disk, config, vec![0; pages * 4096]).unwrap()
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = open(LogDisk::new(256), 16, 0);
+        let mut buf = [0u8; 1024];
+        cache.read_blocks(9, &mut buf).unwrap();
+        assert_eq!(buf[0], 9);
+        assert_eq!(buf[512], 10);
+        cache.read_blocks(10, &mut buf[..512]).unwrap();
+        assert_eq!(buf[0], 10);
+
+        // Pages 1 and 2 in one request
+        let mut two = vec![0u8; 4096];
+        cache.read_blocks(12, &mut two).unwrap();
+        assert_eq!(cache.device.log, [Op::Read(8, 8), Op::Read(16, 8)]);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(stats.hit_rate(), 50);
+
+        let config = CacheConfig {
+            page_size: 1000,
+            ..CacheConfig::default()
+        };
+        let result = BlockCache::with_backing(LogDisk::new(8), config, vec![0; 1000]);
+        assert_eq!(result.err().map(|e| e.status()), Some(Status::InvalidArgs));
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let mut cache = open(LogDisk::new(1024), 64, 16);
+        let mut page = vec![0u8; 4096];
+        for index in 0..64 {
+            cache.read_blocks(index * 8, &mut page).unwrap();
+            assert_eq!(page[0], (index * 8) as u8);
+        }
+        let stats = cache.stats();
+        assert!(cache.device.log.len() < 16, "{:?}", cache.device.log);
+        assert!(stats.read_ahead_hits > 40, "{:?}", stats);
+        assert_eq!(stats.hits + stats.misses, 64);
+
+        // Random reads don't read ahead
+        let mut cache = open(LogDisk::new(1024), 64, 16);
+        for index in [7, 90, 3, 60, 25, 100] {
+            cache.read_blocks(index * 8, &mut page).unwrap();
+        }
+        assert_eq!(cache.stats().read_ahead, 0);
+        assert_eq!(cache.device.log.len(), 6);
+    }
+
+    #[test]
+    fn test_write_back() {
+        let mut cache = open(LogDisk::new(256), 16, 0);
+        cache.write_blocks(8, &[0xAA; 4096]).unwrap();
+        cache.write_blocks(16, &[0xBB; 4096]).unwrap();
+        // Part of a page has to read the rest first
+        cache.write_blocks(25, &[0xCC; 512]).unwrap();
+        assert_eq!(cache.device.log, [Op::Read(24, 8)]);
+        assert_eq!(cache.dirty_pages(), 3);
+
+        let mut buf = [0u8; 1024];
+        cache.read_blocks(24, &mut buf).unwrap();
+        assert_eq!((buf[0], buf[512]), (24, 0xCC));
+        assert_eq!(cache.device.data[8 * 512], 8);
+
+        // One write for the three pages, then the barrier
+        cache.flush().unwrap();
+        assert_eq!(cache.device.log[1..], [Op::Write(8, 24), Op::Flush]);
+        assert_eq!(cache.device.data[8 * 512], 0xAA);
+        assert_eq!(cache.device.data[25 * 512], 0xCC);
+        assert_eq!(cache.device.data[26 * 512], 26);
+        assert_eq!(cache.dirty_pages(), 0);
+        assert_eq!(cache.stats().write_backs, 3);
+        assert_eq!(cache.stats().flushes, 1);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut disk = LogDisk::new(1024);
+        disk.max_transfer = 8192;
+        let mut cache = open(disk, 4, 0);
+        for index in 0..12u64 {
+            cache.write_blocks(index * 8, &[index as u8 + 100; 4096]).unwrap();
+        }
+        assert_eq!(cache.stats().evictions, 8);
+        assert_eq!(cache.dirty_pages(), 4);
+        let mut page = vec![0u8; 4096];
+        cache.read_blocks(0, &mut page).unwrap();
+        assert_eq!(page[4095], 100);
+
+        // Write-back splits runs to the device's largest transfer
+        let disk = cache.into_inner().unwrap();
+        assert!(disk.log.iter().all(|op| match op {
+            Op::Read(_, len) | Op::Write(_, len) => *len <= 16,
+            Op::Flush => true,
+        }));
+        assert_eq!(disk.log.last(), Some(&Op::Flush));
+        for index in 0..12 {
+            assert_eq!(disk.data[index * 4096], index as u8 + 100);
+        }
+    }
+
+    #[test]
+    fn test_uncached_tail() {
+        // 20 blocks: two whole pages and four blocks past them
+        let mut cache = open(LogDisk::new(20), 4, 0);
+        let mut buf = [0u8; 2048];
+        cache.read_blocks(14, &mut buf).unwrap();
+        assert_eq!((buf[0], buf[1024]), (14, 16));
+        cache.write_blocks(16, &[7; 2048]).unwrap();
+        assert_eq!(cache.device.data[19 * 512], 7);
+        assert_eq!(cache.dirty_pages(), 0);
+        assert_eq!(
+            cache.read_blocks(20, &mut buf[..512]).unwrap_err().status(),
+            Status::InvalidArgs
+        );
+    }
+}
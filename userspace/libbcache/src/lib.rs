@@ -0,0 +1,42 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Rustux Block Cache
+//!
+//! A page cache for filesystem servers, in front of any DDK `BlockDevice`:
+//! - Pages kept in a mapped VMO and evicted least recently used first
+//! - Read-ahead that grows with sequential streams and stays off for
+//!   random access
+//! - Write-back, with `flush` as the barrier: it writes every dirty page
+//!   and then sends the device a FLUSH
+//! - Hit, miss, read-ahead and write-back counts
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use bcache::{BlockCache, CacheConfig};
+//! use libddk::BlockDevice;
+//!
+//! fn read_twice<D: BlockDevice>(disk: D) -> libsys::Result<()> {
+//!     let mut cache = BlockCache::new(disk, CacheConfig::default())?;
+//!     let mut block = [0u8; 512];
+//!     cache.read_blocks(0, &mut block)?;
+//!     cache.read_blocks(0, &mut block)?;
+//!     assert_eq!(cache.stats().hits, 1);
+//!     cache.flush()
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod cache;
+pub mod lru;
+pub mod readahead;
+
+// Re-export commonly used types
+pub use cache::{Backing, BlockCache, CacheConfig, Stats};
@@ -0,0 +1,118 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! LRU Order
+//!
+//! A doubly linked list threaded through slot indices, most recently used
+//! first, so moving a slot to the front or taking the oldest is O(1).
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// No slot
+const NIL: usize = usize::MAX;
+
+/// Slots in order of use
+pub struct Lru {
+    prev: Vec<usize>,
+    next: Vec<usize>,
+
+    /// Most recently used
+    head: usize,
+
+    /// Least recently used
+    tail: usize,
+}
+
+impl Lru {
+    /// An empty order over `slots` slots
+    pub fn new(slots: usize) -> Self {
+        Self {
+            prev: vec![NIL; slots],
+            next: vec![NIL; slots],
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    fn linked(&self, slot: usize) -> bool {
+        self.prev[slot] != NIL || self.head == slot
+    }
+
+    /// Take `slot` out of the order, if it is in it
+    pub fn remove(&mut self, slot: usize) {
+        if !self.linked(slot) {
+            return;
+        }
+        let (prev, next) = (self.prev[slot], self.next[slot]);
+        match prev {
+            NIL => self.head = next,
+            prev => self.next[prev] = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.prev[next] = prev,
+        }
+        self.prev[slot] = NIL;
+        self.next[slot] = NIL;
+    }
+
+    /// Make `slot` the most recently used
+    pub fn touch(&mut self, slot: usize) {
+        self.remove(slot);
+        self.next[slot] = self.head;
+        match self.head {
+            NIL => self.tail = slot,
+            head => self.prev[head] = slot,
+        }
+        self.head = slot;
+    }
+
+    /// The least recently used slot
+    pub fn oldest(&self) -> Option<usize> {
+        (self.tail != NIL).then_some(self.tail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(lru: &Lru) -> Vec<usize> {
+        let mut slots = Vec::new();
+        let mut slot = lru.head;
+        while slot != NIL {
+            slots.push(slot);
+            slot = lru.next[slot];
+        }
+        slots
+    }
+
+    #[test]
+    fn test_order() {
+        let mut lru = Lru::new(4);
+        assert_eq!(lru.oldest(), None);
+        for slot in 0..4 {
+            lru.touch(slot);
+        }
+        assert_eq!(order(&lru), [3, 2, 1, 0]);
+        assert_eq!(lru.oldest(), Some(0));
+
+        lru.touch(0);
+        lru.touch(2);
+        assert_eq!(order(&lru), [2, 0, 3, 1]);
+        assert_eq!(lru.oldest(), Some(1));
+
+        lru.remove(1);
+        lru.remove(1);
+        lru.remove(2);
+        assert_eq!(order(&lru), [0, 3]);
+        assert_eq!(lru.oldest(), Some(3));
+        lru.remove(0);
+        lru.remove(3);
+        assert_eq!(lru.oldest(), None);
+    }
+}
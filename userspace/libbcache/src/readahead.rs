@@ -0,0 +1,78 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Read-Ahead
+//!
+//! A read that starts where the last one ended is taken as part of a
+//! sequential stream. Each one in a row doubles the window of pages kept
+//! cached ahead of the reader, up to the configured limit; any other read
+//! closes the window, so random access costs no extra I/O.
+
+/// Window opened by the second read in a row, in pages
+pub const MIN_WINDOW: u64 = 4;
+
+/// Sequential stream detection
+#[derive(Debug, Clone, Copy)]
+pub struct ReadAhead {
+    /// Page after the last read
+    next: Option<u64>,
+
+    window: u64,
+    max: u64,
+}
+
+impl ReadAhead {
+    /// Read ahead up to `max` pages; zero turns read-ahead off
+    pub fn new(max: u64) -> Self {
+        Self {
+            next: None,
+            window: 0,
+            max,
+        }
+    }
+
+    /// Record a read of pages `first..end`, returning how many pages past
+    /// `end` should be cached
+    pub fn access(&mut self, first: u64, end: u64) -> u64 {
+        self.window = match self.next == Some(first) {
+            true => (self.window * 2).max(MIN_WINDOW).min(self.max),
+            false => 0,
+        };
+        self.next = Some(end);
+        self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential() {
+        let mut read_ahead = ReadAhead::new(32);
+        assert_eq!(read_ahead.access(0, 1), 0);
+        assert_eq!(read_ahead.access(1, 2), 4);
+        assert_eq!(read_ahead.access(2, 4), 8);
+        assert_eq!(read_ahead.access(4, 5), 16);
+        assert_eq!(read_ahead.access(5, 6), 32);
+        assert_eq!(read_ahead.access(6, 7), 32);
+
+        // A jump closes the window; the stream has to start over
+        assert_eq!(read_ahead.access(100, 101), 0);
+        assert_eq!(read_ahead.access(101, 102), 4);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut off = ReadAhead::new(0);
+        off.access(0, 1);
+        assert_eq!(off.access(1, 2), 0);
+
+        let mut small = ReadAhead::new(2);
+        small.access(0, 1);
+        assert_eq!(small.access(1, 2), 2);
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Error numbers
+//!
+//! The `errno` values from errno.h, with their Linux numbering, and the
+//! mapping from libsys statuses to them.
+
+use super::c_int;
+use libsys::Status;
+
+/// Operation not permitted
+pub const EPERM: c_int = 1;
+/// No such file or directory
+pub const ENOENT: c_int = 2;
+/// Interrupted system call
+pub const EINTR: c_int = 4;
+/// I/O error
+pub const EIO: c_int = 5;
+/// Bad file descriptor
+pub const EBADF: c_int = 9;
+/// Try again
+pub const EAGAIN: c_int = 11;
+/// Out of memory
+pub const ENOMEM: c_int = 12;
+/// Permission denied
+pub const EACCES: c_int = 13;
+/// Device or resource busy
+pub const EBUSY: c_int = 16;
+/// File exists
+pub const EEXIST: c_int = 17;
+/// Not a directory
+pub const ENOTDIR: c_int = 20;
+/// Is a directory
+pub const EISDIR: c_int = 21;
+/// Invalid argument
+pub const EINVAL: c_int = 22;
+/// Too many open files
+pub const EMFILE: c_int = 24;
+/// Not a typewriter
+pub const ENOTTY: c_int = 25;
+/// Illegal seek
+pub const ESPIPE: c_int = 29;
+/// Broken pipe
+pub const EPIPE: c_int = 32;
+/// Function not implemented
+pub const ENOSYS: c_int = 38;
+/// Operation not supported
+pub const ENOTSUP: c_int = 95;
+/// Connection timed out
+pub const ETIMEDOUT: c_int = 110;
+
+/// Operation would block
+pub const EWOULDBLOCK: c_int = EAGAIN;
+
+/// The `errno` value for a failed libsys call
+pub fn from_status(status: Status) -> c_int {
+    match status {
+        Status::Ok => 0,
+        Status::NoMemory => ENOMEM,
+        Status::NotSupported => ENOTSUP,
+        Status::InvalidArgs | Status::BufferTooSmall => EINVAL,
+        Status::NotFound => ENOENT,
+        Status::AlreadyExists => EEXIST,
+        Status::WouldBlock => EAGAIN,
+        Status::AccessDenied => EACCES,
+        Status::TimedOut => ETIMEDOUT,
+        Status::HandleClosed | Status::WrongType => EBADF,
+        Status::Busy => EBUSY,
+        Status::PeerClosed => EPIPE,
+        Status::IoError | Status::BadState | Status::Internal => EIO,
+    }
+}
+
+/// Set `errno`
+pub fn set_errno(errno: c_int) {
+    unsafe {
+        *super::__errno() = errno;
+    }
+}
+
+/// The value of a successful call, or `failed` with `errno` set
+pub fn or_fail<T>(result: Result<T, c_int>, failed: T) -> T {
+    result.unwrap_or_else(|errno| {
+        set_errno(errno);
+        failed
+    })
+}
@@ -0,0 +1,88 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! File control functions
+//!
+//! This module provides `open` and `fcntl` from fcntl.h, on the descriptor
+//! table in `fdio`. Flag values follow Linux.
+
+use super::{c_char, c_int};
+use crate::errno::{or_fail, set_errno, EINVAL};
+use crate::fdio;
+
+/// Open for reading only
+pub const O_RDONLY: c_int = 0o0;
+/// Open for writing only
+pub const O_WRONLY: c_int = 0o1;
+/// Open for reading and writing
+pub const O_RDWR: c_int = 0o2;
+/// Mask of the access mode
+pub const O_ACCMODE: c_int = 0o3;
+/// Create the file if it doesn't exist
+pub const O_CREAT: c_int = 0o100;
+/// With `O_CREAT`, fail if the file exists
+pub const O_EXCL: c_int = 0o200;
+/// Truncate the file to 0 bytes
+pub const O_TRUNC: c_int = 0o1000;
+/// Write at the end of the file
+pub const O_APPEND: c_int = 0o2000;
+/// Fail with `EAGAIN` instead of waiting
+pub const O_NONBLOCK: c_int = 0o4000;
+/// Fail unless the path is a directory
+pub const O_DIRECTORY: c_int = 0o200000;
+/// Set `FD_CLOEXEC` on the new descriptor
+pub const O_CLOEXEC: c_int = 0o2000000;
+
+/// Duplicate a descriptor to the lowest free one from the argument
+pub const F_DUPFD: c_int = 0;
+/// Get the descriptor flags
+pub const F_GETFD: c_int = 1;
+/// Set the descriptor flags
+pub const F_SETFD: c_int = 2;
+/// Get the access mode and status flags
+pub const F_GETFL: c_int = 3;
+/// Set the status flags
+pub const F_SETFL: c_int = 4;
+/// `F_DUPFD`, setting `FD_CLOEXEC` on the new descriptor
+pub const F_DUPFD_CLOEXEC: c_int = 1030;
+
+/// Close the descriptor when the process execs
+pub const FD_CLOEXEC: c_int = 1;
+
+/// Open a file
+///
+/// The mode of a created file is ignored; Rustux filesystems have no
+/// permission bits.
+#[no_mangle]
+pub unsafe extern "C" fn open(path: *const c_char, flags: c_int, _mode: ...) -> c_int {
+    if path.is_null() {
+        set_errno(EINVAL);
+        return -1;
+    }
+
+    let path = core::ffi::CStr::from_ptr(path);
+    or_fail(fdio::open(path.to_bytes(), flags), -1)
+}
+
+/// Create or truncate a file for writing
+#[no_mangle]
+pub unsafe extern "C" fn creat(path: *const c_char, _mode: u32) -> c_int {
+    open(path, O_WRONLY | O_CREAT | O_TRUNC)
+}
+
+/// Manipulate a file descriptor
+#[no_mangle]
+pub unsafe extern "C" fn fcntl(fd: c_int, cmd: c_int, mut args: ...) -> c_int {
+    let result = match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => fdio::dup(fd, args.arg::<c_int>(), cmd == F_DUPFD_CLOEXEC),
+        F_GETFD => fdio::cloexec(fd).map(|cloexec| if cloexec { FD_CLOEXEC } else { 0 }),
+        F_SETFD => fdio::set_cloexec(fd, args.arg::<c_int>() & FD_CLOEXEC != 0).map(|()| 0),
+        F_GETFL => fdio::status_flags(fd),
+        F_SETFL => fdio::set_status_flags(fd, args.arg::<c_int>()).map(|()| 0),
+        _ => Err(EINVAL),
+    };
+    or_fail(result, -1)
+}
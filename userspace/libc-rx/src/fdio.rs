@@ -0,0 +1,567 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! File descriptors (fdio)
+//!
+//! C code names open files by small integers; Rustux names objects by
+//! handles. This module keeps the table between the two. A descriptor
+//! refers to an open file description, which `dup` shares, holding the
+//! object, the file offset and the status flags. The objects are:
+//! - Files and directories, opened by path on the filesystem server at
+//!   the namespace root, over the Vfs protocol
+//! - Sockets, the two ends of a `pipe`
+//! - VMOs, read and written like files
+//! - Channels, where each write sends a message and each read takes one
+//! - The debug console
+//!
+//! The process's startup handles fill the table (see `__libc_init_fdio`);
+//! descriptors 0, 1 and 2 are the debug console unless a startup handle
+//! provides them.
+
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+use ipc::Channel;
+use libsys::syscall::{syscall1, SyscallNumber};
+use libsys::{startup, Error, Handle, Rights, Socket, Status, Vmo};
+use rt::{Mutex, Thread};
+use vfs::{node_kind, open_flags, VfsProxy, MAX_TRANSFER};
+
+use super::c_int;
+use crate::errno::*;
+use crate::fcntl::*;
+use crate::unistd::{SEEK_CUR, SEEK_END, SEEK_SET};
+
+/// A result carrying an `errno` value on failure
+pub type Errno<T> = core::result::Result<T, c_int>;
+
+/// Most descriptors a process can have open
+pub const MAX_FDS: usize = 256;
+
+/// Status flags `fcntl(F_SETFL)` can change
+const SETTABLE_FLAGS: c_int = O_APPEND | O_NONBLOCK;
+
+pub use libsys::startup::handle_type;
+
+/// The info word of a startup handle of `kind` for descriptor `fd`
+pub const fn handle_info(kind: u32, fd: u16) -> u32 {
+    kind | ((fd as u32) << 16)
+}
+
+fn errno(error: Error) -> c_int {
+    from_status(error.status())
+}
+
+/// What a descriptor refers to
+pub enum Object {
+    /// The kernel debug console; reading it finds end of file
+    Console,
+
+    /// A node opened on the namespace root
+    File {
+        node: u32,
+        directory: bool,
+    },
+
+    /// One end of a pipe
+    Pipe(Socket),
+
+    Vmo(Vmo),
+    Channel(Channel),
+}
+
+/// An open file description
+struct Description {
+    object: Object,
+    offset: u64,
+
+    /// Access mode and status flags (`O_*`)
+    flags: c_int,
+
+    /// Descriptors referring to this description
+    refs: usize,
+}
+
+impl Description {
+    fn new(object: Object, flags: c_int) -> Self {
+        Self {
+            object,
+            offset: 0,
+            flags: flags & (O_ACCMODE | SETTABLE_FLAGS),
+            refs: 0,
+        }
+    }
+
+    fn readable(&self) -> bool {
+        self.flags & O_ACCMODE != O_WRONLY
+    }
+
+    fn writable(&self) -> bool {
+        self.flags & O_ACCMODE != O_RDONLY
+    }
+
+    /// Bytes in the file, for objects that have a size
+    fn size(&self, root: &mut Option<VfsProxy>) -> Errno<u64> {
+        match &self.object {
+            Object::File { node, .. } => {
+                let root = root.as_mut().ok_or(EBADF)?;
+                Ok(root.stat(*node).map_err(errno)?.size)
+            }
+            Object::Vmo(vmo) => vmo.get_size().map_err(errno),
+            _ => Err(ESPIPE),
+        }
+    }
+
+    fn read(&mut self, root: &mut Option<VfsProxy>, buf: &mut [u8]) -> Errno<usize> {
+        if !self.readable() {
+            return Err(EBADF);
+        }
+        let read = match &self.object {
+            Object::Console => 0,
+            Object::File { directory: true, .. } => return Err(EISDIR),
+            Object::File { node, .. } => {
+                let root = root.as_mut().ok_or(EBADF)?;
+                let len = buf.len().min(MAX_TRANSFER as usize) as u32;
+                let data = root.read(*node, self.offset, len).map_err(errno)?;
+                let read = data.len().min(buf.len());
+                buf[..read].copy_from_slice(&data[..read]);
+                read
+            }
+            Object::Pipe(socket) => match socket.read(buf) {
+                Err(e) if e.status() == Status::PeerClosed => 0,
+                result => result.map_err(errno)?,
+            },
+            Object::Vmo(vmo) => {
+                let size = vmo.get_size().map_err(errno)?;
+                let read = size.saturating_sub(self.offset).min(buf.len() as u64) as usize;
+                if read > 0 {
+                    vmo.read(self.offset, &mut buf[..read]).map_err(errno)?;
+                }
+                read
+            }
+            Object::Channel(channel) => match channel.read(buf, &mut Vec::new()) {
+                Err(e) if e.status() == Status::PeerClosed => 0,
+                result => result.map_err(errno)?,
+            },
+        };
+        self.offset += read as u64;
+        Ok(read)
+    }
+
+    fn write(&mut self, root: &mut Option<VfsProxy>, buf: &[u8]) -> Errno<usize> {
+        if !self.writable() {
+            return Err(EBADF);
+        }
+        if self.flags & O_APPEND != 0 && matches!(self.object, Object::File { .. } | Object::Vmo(_)) {
+            self.offset = self.size(root)?;
+        }
+        let written = match &self.object {
+            Object::Console => {
+                for &b in buf {
+                    unsafe {
+                        syscall1(SyscallNumber::WriteStdio as u64, b as u64);
+                    }
+                }
+                buf.len()
+            }
+            Object::File { directory: true, .. } => return Err(EISDIR),
+            Object::File { node, .. } => {
+                let root = root.as_mut().ok_or(EBADF)?;
+                let mut written = 0;
+                for chunk in buf.chunks(MAX_TRANSFER as usize) {
+                    let offset = self.offset + written as u64;
+                    let done = match root.write(*node, offset, chunk.to_vec()) {
+                        Ok(done) => done as usize,
+                        // Report what made it before the error
+                        Err(_) if written > 0 => break,
+                        Err(e) => return Err(errno(e)),
+                    };
+                    written += done;
+                    if done < chunk.len() {
+                        break;
+                    }
+                }
+                written
+            }
+            Object::Pipe(socket) => socket.write(buf).map_err(errno)?,
+            Object::Vmo(vmo) => {
+                let end = self.offset + buf.len() as u64;
+                if end > vmo.get_size().map_err(errno)? {
+                    vmo.set_size(end).map_err(errno)?;
+                }
+                vmo.write(self.offset, buf).map_err(errno)?;
+                buf.len()
+            }
+            Object::Channel(channel) => {
+                channel.write(buf, &[]).map_err(errno)?;
+                buf.len()
+            }
+        };
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn seek(&mut self, root: &mut Option<VfsProxy>, offset: i64, whence: c_int) -> Errno<u64> {
+        if !matches!(self.object, Object::File { .. } | Object::Vmo(_)) {
+            return Err(ESPIPE);
+        }
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => self.offset,
+            SEEK_END => self.size(root)?,
+            _ => return Err(EINVAL),
+        };
+        let offset = (base as i64)
+            .checked_add(offset)
+            .filter(|&offset| offset >= 0)
+            .ok_or(EINVAL)?;
+        self.offset = offset as u64;
+        Ok(self.offset)
+    }
+}
+
+/// A descriptor
+#[derive(Debug, Clone, Copy)]
+struct Fd {
+    /// Index of its description
+    description: usize,
+
+    /// `FD_CLOEXEC`
+    cloexec: bool,
+}
+
+/// Descriptors and the descriptions they refer to
+struct FdTable {
+    fds: Vec<Option<Fd>>,
+    descriptions: Vec<Option<Description>>,
+    root: Option<VfsProxy>,
+}
+
+impl FdTable {
+    const fn new() -> Self {
+        Self {
+            fds: Vec::new(),
+            descriptions: Vec::new(),
+            root: None,
+        }
+    }
+
+    fn fd(&self, fd: c_int) -> Errno<Fd> {
+        let fd = usize::try_from(fd).map_err(|_| EBADF)?;
+        self.fds.get(fd).copied().flatten().ok_or(EBADF)
+    }
+
+    /// `fd`'s description, with the namespace root its methods take
+    fn open_file(&mut self, fd: c_int) -> Errno<(&mut Description, &mut Option<VfsProxy>)> {
+        let index = self.fd(fd)?.description;
+        let description = self.descriptions[index]
+            .as_mut()
+            .expect("descriptor without a description");
+        Ok((description, &mut self.root))
+    }
+
+    fn description(&mut self, fd: c_int) -> Errno<&mut Description> {
+        self.open_file(fd).map(|(description, _)| description)
+    }
+
+    /// The lowest free descriptor from `min`
+    fn free_fd(&self, min: c_int) -> Errno<c_int> {
+        let min = usize::try_from(min).ok().filter(|&min| min < MAX_FDS).ok_or(EINVAL)?;
+        (min..MAX_FDS)
+            .find(|&fd| self.fds.get(fd).is_none_or(Option::is_none))
+            .map(|fd| fd as c_int)
+            .ok_or(EMFILE)
+    }
+
+    /// Point `fd`, which must be free, at description `index`
+    fn set(&mut self, fd: c_int, index: usize, cloexec: bool) {
+        let fd = fd as usize;
+        if self.fds.len() <= fd {
+            self.fds.resize(fd + 1, None);
+        }
+        self.fds[fd] = Some(Fd {
+            description: index,
+            cloexec,
+        });
+        if let Some(description) = self.descriptions[index].as_mut() {
+            description.refs += 1;
+        }
+    }
+
+    /// Give `description` the lowest free descriptor from `min`
+    fn install(&mut self, description: Description, min: c_int, cloexec: bool) -> Errno<c_int> {
+        let fd = self.free_fd(min)?;
+        let index = match self.descriptions.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.descriptions.push(None);
+                self.descriptions.len() - 1
+            }
+        };
+        self.descriptions[index] = Some(description);
+        self.set(fd, index, cloexec);
+        Ok(fd)
+    }
+
+    /// Free `fd`, releasing its description's object if it was the last
+    /// descriptor referring to it
+    fn close(&mut self, fd: c_int) -> Errno<()> {
+        let index = self.fd(fd)?.description;
+        self.fds[fd as usize] = None;
+        let description = self.descriptions[index]
+            .as_mut()
+            .expect("descriptor without a description");
+        description.refs -= 1;
+        if description.refs > 0 {
+            return Ok(());
+        }
+        match self.descriptions[index].take().map(|description| description.object) {
+            Some(Object::File { node, .. }) => match self.root.as_mut() {
+                Some(root) => root.close(node).map_err(errno),
+                None => Ok(()),
+            },
+            // Dropping the rest closes their handles
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The process's descriptor table
+struct Fdio {
+    lock: Mutex,
+    table: UnsafeCell<FdTable>,
+}
+
+// SAFETY: the table is only reached through `with_table`, under the lock
+unsafe impl Sync for Fdio {}
+
+static FDIO: Fdio = Fdio {
+    lock: Mutex::new(),
+    table: UnsafeCell::new(FdTable::new()),
+};
+
+/// Run `f` on the descriptor table with it locked
+fn with_table<T>(f: impl FnOnce(&mut FdTable) -> T) -> T {
+    let _guard = FDIO.lock.lock();
+    f(unsafe { &mut *FDIO.table.get() })
+}
+
+/// Run `attempt` on `fd`'s description until it stops failing with
+/// `EAGAIN`, unless the description is non-blocking
+///
+/// The table is unlocked between attempts, so other threads can reach the
+/// other end of a pipe.
+fn blocking(
+    fd: c_int,
+    mut attempt: impl FnMut(&mut Description, &mut Option<VfsProxy>) -> Errno<usize>,
+) -> Errno<usize> {
+    loop {
+        let (result, nonblock) = with_table(|table| {
+            let (description, root) = table.open_file(fd)?;
+            Ok::<_, c_int>((attempt(description, root), description.flags & O_NONBLOCK != 0))
+        })?;
+        match result {
+            Err(EAGAIN) if !nonblock => Thread::yield_now(),
+            result => return result,
+        }
+    }
+}
+
+/// Make `root` the namespace root `open` resolves paths on
+pub fn set_root(root: VfsProxy) {
+    with_table(|table| table.root = Some(root));
+}
+
+/// Give `object` the lowest free descriptor, opened with `flags` (`O_*`)
+pub fn install(object: Object, flags: c_int) -> Errno<c_int> {
+    with_table(|table| table.install(Description::new(object, flags), 0, flags & O_CLOEXEC != 0))
+}
+
+/// Open `path` on the namespace root with `flags` (`O_*`)
+pub fn open(path: &[u8], flags: c_int) -> Errno<c_int> {
+    let mut vfs_flags = match flags & O_ACCMODE {
+        O_RDONLY => open_flags::READ,
+        O_WRONLY => open_flags::WRITE,
+        O_RDWR => open_flags::READ | open_flags::WRITE,
+        _ => return Err(EINVAL),
+    };
+    for (flag, vfs_flag) in [
+        (O_CREAT, open_flags::CREATE),
+        (O_EXCL, open_flags::EXCLUSIVE),
+        (O_TRUNC, open_flags::TRUNCATE),
+        (O_DIRECTORY, open_flags::DIRECTORY),
+    ] {
+        if flags & flag != 0 {
+            vfs_flags |= vfs_flag;
+        }
+    }
+
+    with_table(|table| {
+        // Fail before opening anything if there's no room
+        table.free_fd(0)?;
+        let root = table.root.as_mut().ok_or(ENOENT)?;
+        let node = root.open(path.to_vec(), vfs_flags).map_err(errno)?;
+        let directory = match root.stat(node) {
+            Ok(attr) => attr.kind == node_kind::DIRECTORY,
+            Err(e) => {
+                let _ = root.close(node);
+                return Err(errno(e));
+            }
+        };
+        let description = Description::new(Object::File { node, directory }, flags);
+        table.install(description, 0, flags & O_CLOEXEC != 0)
+    })
+}
+
+/// Read into `buf` at `fd`'s offset, waiting for a pipe or channel to
+/// have data unless `fd` is non-blocking
+pub fn read(fd: c_int, buf: &mut [u8]) -> Errno<usize> {
+    blocking(fd, |description, root| description.read(root, buf))
+}
+
+/// Write `buf` at `fd`'s offset, or at the end with `O_APPEND`
+pub fn write(fd: c_int, buf: &[u8]) -> Errno<usize> {
+    blocking(fd, |description, root| description.write(root, buf))
+}
+
+/// Move `fd`'s offset, returning the new one
+pub fn seek(fd: c_int, offset: i64, whence: c_int) -> Errno<u64> {
+    with_table(|table| {
+        let (description, root) = table.open_file(fd)?;
+        description.seek(root, offset, whence)
+    })
+}
+
+/// Free `fd`, closing its object with the last descriptor referring to it
+pub fn close(fd: c_int) -> Errno<()> {
+    with_table(|table| table.close(fd))
+}
+
+/// A new descriptor for `fd`'s description, the lowest free one from `min`
+pub fn dup(fd: c_int, min: c_int, cloexec: bool) -> Errno<c_int> {
+    with_table(|table| {
+        let index = table.fd(fd)?.description;
+        let new = table.free_fd(min)?;
+        table.set(new, index, cloexec);
+        Ok(new)
+    })
+}
+
+/// Make `new` refer to `old`'s description, closing what `new` referred
+/// to first
+pub fn dup2(old: c_int, new: c_int) -> Errno<c_int> {
+    with_table(|table| {
+        let index = table.fd(old)?.description;
+        if !(0..MAX_FDS as c_int).contains(&new) {
+            return Err(EBADF);
+        }
+        if old == new {
+            return Ok(new);
+        }
+        if table.fd(new).is_ok() {
+            // Like Linux, errors closing the old file are ignored
+            let _ = table.close(new);
+        }
+        table.set(new, index, false);
+        Ok(new)
+    })
+}
+
+/// Whether `fd` is closed when the process execs
+pub fn cloexec(fd: c_int) -> Errno<bool> {
+    with_table(|table| Ok(table.fd(fd)?.cloexec))
+}
+
+/// Set whether `fd` is closed when the process execs
+pub fn set_cloexec(fd: c_int, cloexec: bool) -> Errno<()> {
+    with_table(|table| {
+        table.fd(fd)?;
+        if let Some(entry) = table.fds[fd as usize].as_mut() {
+            entry.cloexec = cloexec;
+        }
+        Ok(())
+    })
+}
+
+/// `fd`'s access mode and status flags
+pub fn status_flags(fd: c_int) -> Errno<c_int> {
+    with_table(|table| Ok(table.description(fd)?.flags))
+}
+
+/// Set `fd`'s `O_APPEND` and `O_NONBLOCK` from `flags`; the rest are
+/// ignored
+pub fn set_status_flags(fd: c_int, flags: c_int) -> Errno<()> {
+    with_table(|table| {
+        let description = table.description(fd)?;
+        description.flags = (description.flags & !SETTABLE_FLAGS) | (flags & SETTABLE_FLAGS);
+        Ok(())
+    })
+}
+
+/// Whether `fd` is the debug console
+pub fn is_console(fd: c_int) -> Errno<bool> {
+    with_table(|table| Ok(matches!(table.description(fd)?.object, Object::Console)))
+}
+
+/// A pipe on a new socket pair, as its read and write descriptors
+pub fn pipe(cloexec: bool) -> Errno<[c_int; 2]> {
+    let (reader, writer) = Socket::create().map_err(errno)?;
+    // Each end only goes one way
+    reader.shutdown(Socket::SHUTDOWN_WRITE).map_err(errno)?;
+    writer.shutdown(Socket::SHUTDOWN_READ).map_err(errno)?;
+    with_table(|table| {
+        let read = table.install(Description::new(Object::Pipe(reader), O_RDONLY), 0, cloexec)?;
+        match table.install(Description::new(Object::Pipe(writer), O_WRONLY), 0, cloexec) {
+            Ok(write) => Ok([read, write]),
+            Err(e) => {
+                let _ = table.close(read);
+                Err(e)
+            }
+        }
+    })
+}
+
+/// Fill the descriptor table from the startup handles
+///
+/// Takes the namespace root and the descriptor handles (see `handle_info`)
+/// from those [`libsys::startup::init`] recorded, leaving the other types
+/// for the program. Descriptors 0, 1 and 2 that no handle provides become
+/// the debug console. The C runtime calls this before `main`.
+#[no_mangle]
+pub extern "C" fn __libc_init_fdio() {
+    if let Some((handle, _)) = startup::take(handle_type::NS_ROOT, libsys::Channel::DEFAULT_RIGHTS) {
+        set_root(VfsProxy::new(unsafe { Channel::from_handle(handle) }));
+    }
+    while let Some((handle, info)) = startup::take(handle_type::FD_VMO, Vmo::DEFAULT_RIGHTS) {
+        install_startup(Object::Vmo(unsafe { Vmo::from_handle(handle) }), info);
+    }
+    while let Some((handle, info)) =
+        startup::take(handle_type::FD_CHANNEL, libsys::Channel::DEFAULT_RIGHTS)
+    {
+        install_startup(Object::Channel(unsafe { Channel::from_handle(handle) }), info);
+    }
+
+    with_table(|table| {
+        for fd in 0..3 {
+            if table.fd(fd).is_err() {
+                let flags = if fd == 0 { O_RDONLY } else { O_WRONLY };
+                let _ = table.install(Description::new(Object::Console, flags), fd, false);
+            }
+        }
+    });
+}
+
+/// Open a startup handle as the descriptor in its info word's high half
+///
+/// The object is closed if that descriptor is out of range or taken.
+fn install_startup(object: Object, info: u32) {
+    let fd = (info >> 16) as c_int;
+    with_table(|table| {
+        if (0..MAX_FDS as c_int).contains(&fd) && table.fd(fd).is_err() {
+            let _ = table.install(Description::new(object, O_RDWR), fd, false);
+        }
+    });
+}
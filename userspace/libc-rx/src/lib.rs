@@ -15,15 +15,26 @@
 //! - **stdio** - Standard I/O functions (printf, FILE*, etc.)
 //! - **stdlib** - Standard library functions (malloc, free, atoi, etc.)
 //! - **unistd** - POSIX-standard functions (read, write, etc.)
+//! - **fcntl** - File control functions (open, fcntl)
+//! - **fdio** - The file descriptor table, mapping descriptors to channels,
+//!   sockets and VMOs
+//! - **errno** - Error numbers
+//! - **ssp** - Stack smashing protection (`__stack_chk_guard`, `__stack_chk_fail`)
 
 #![no_std]
 #![feature(c_variadic)]
 #![feature(ffi_asm)]
 
+extern crate alloc;
+
 pub mod string;
 pub mod stdio;
 pub mod stdlib;
 pub mod unistd;
+pub mod fcntl;
+pub mod fdio;
+pub mod errno;
+pub mod ssp;
 
 // Re-export commonly used C types
 pub use stdio::{FILE, stdin, stdout, stderr};
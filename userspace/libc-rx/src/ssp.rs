@@ -0,0 +1,68 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Stack Smashing Protection
+//!
+//! Guard value and failure handler for code built with
+//! `-fstack-protector*`. Userspace uses the global ABI: instrumented
+//! functions compare their canary against `__stack_chk_guard`.
+
+use crate::{c_int, uintptr_t};
+
+/// Bits of the guard that are forced to zero
+///
+/// A zero low byte stops string functions from reading the guard out or
+/// writing it back during an overflow.
+const STACK_GUARD_MASK: uintptr_t = !0xff;
+
+/// Exit status used when a canary check fails
+const STACK_CHK_FAIL_STATUS: c_int = 127;
+
+/// Process-wide stack guard
+///
+/// Set once by crt startup before `main`; changing it later would trip the
+/// canary of every function already on the stack.
+#[no_mangle]
+pub static mut __stack_chk_guard: uintptr_t = 0x595e_9fbd_94fd_a700;
+
+/// Called by instrumented code when a canary doesn't match
+///
+/// The stack can't be trusted any more, so this exits straight away
+/// without running any cleanup.
+#[no_mangle]
+pub extern "C" fn __stack_chk_fail() -> ! {
+    unsafe {
+        libsys::Process::exit(STACK_CHK_FAIL_STATUS);
+    }
+}
+
+/// Initialize `__stack_chk_guard`
+///
+/// `random` points to the 16 `AT_RANDOM` bytes the kernel placed on the
+/// initial stack, or is null if there were none, in which case the guard is
+/// drawn from the kernel CPRNG. The guard is written in place so that this
+/// function has no local buffer of its own to protect.
+///
+/// # Safety
+///
+/// Must be called once, from crt startup, before any instrumented code.
+/// `random` must be null or point to at least `size_of::<uintptr_t>()`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn __libc_init_stack_guard(random: *const u8) {
+    let guard = core::ptr::addr_of_mut!(__stack_chk_guard);
+
+    if random.is_null() {
+        libsys::cprng::draw(core::slice::from_raw_parts_mut(
+            guard as *mut u8,
+            core::mem::size_of::<uintptr_t>(),
+        ));
+    } else {
+        *guard = core::ptr::read_unaligned(random as *const uintptr_t);
+    }
+
+    *guard &= STACK_GUARD_MASK;
+}
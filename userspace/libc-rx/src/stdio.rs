@@ -11,6 +11,7 @@
 #![no_std]
 
 use super::c_int;
+use crate::unistd::write;
 use core::ffi::c_char;
 use core::fmt;
 
@@ -41,35 +42,6 @@ pub static mut stdout: *mut FILE = core::ptr::null_mut();
 #[no_mangle]
 pub static mut stderr: *mut FILE = core::ptr::null_mut();
 
-// Internal write function
-extern "C" {
-    fn sys_write(fd: c_int, buf: *const u8, count: usize) -> isize;
-}
-
-/// Write to a file descriptor
-#[no_mangle]
-pub unsafe extern "C" fn write(fd: c_int, buf: *const u8, count: usize) -> isize {
-    if buf.is_null() || count == 0 {
-        return 0;
-    }
-
-    // TODO: Implement actual write syscall
-    // For now, this is a stub
-    count as isize
-}
-
-/// Read from a file descriptor
-#[no_mangle]
-pub unsafe extern "C" fn read(fd: c_int, buf: *mut u8, count: usize) -> isize {
-    if buf.is_null() || count == 0 {
-        return 0;
-    }
-
-    // TODO: Implement actual read syscall
-    // For now, this is a stub
-    0
-}
-
 /// Write formatted output to stdout
 #[no_mangle]
 pub unsafe extern "C" fn printf(format: *const c_char, args: ...) -> c_int {
@@ -11,7 +11,10 @@
 #![no_std]
 
 use super::{c_int, size_t};
-use core::ptr;
+use crate::errno::{or_fail, set_errno, EINVAL, ENOTTY};
+use crate::fcntl::O_CLOEXEC;
+use crate::fdio;
+use core::{ptr, slice};
 
 /// File descriptor for standard input
 pub const STDIN_FILENO: c_int = 0;
@@ -30,53 +33,88 @@ pub const SEEK_END: c_int = 2;
 /// Read from a file descriptor
 #[no_mangle]
 pub unsafe extern "C" fn read(fd: c_int, buf: *mut u8, count: size_t) -> isize {
-    if buf.is_null() || count == 0 {
+    if count == 0 {
         return 0;
     }
+    if buf.is_null() {
+        set_errno(EINVAL);
+        return -1;
+    }
 
-    // TODO: Implement actual read syscall
-    // For now, return 0 (EOF)
-    0
+    let buf = slice::from_raw_parts_mut(buf, count);
+    or_fail(fdio::read(fd, buf).map(|read| read as isize), -1)
 }
 
 /// Write to a file descriptor
 #[no_mangle]
 pub unsafe extern "C" fn write(fd: c_int, buf: *const u8, count: size_t) -> isize {
-    if buf.is_null() || count == 0 {
+    if count == 0 {
         return 0;
     }
+    if buf.is_null() {
+        set_errno(EINVAL);
+        return -1;
+    }
 
-    // TODO: Implement actual write syscall
-    // For now, just return count (pretend we wrote everything)
-    count as isize
+    let buf = slice::from_raw_parts(buf, count);
+    or_fail(fdio::write(fd, buf).map(|written| written as isize), -1)
 }
 
 /// Reposition file offset
 #[no_mangle]
 pub unsafe extern "C" fn lseek(fd: c_int, offset: isize, whence: c_int) -> isize {
-    // TODO: Implement lseek syscall
-    -1
+    or_fail(fdio::seek(fd, offset as i64, whence).map(|offset| offset as isize), -1)
 }
 
 /// Close a file descriptor
 #[no_mangle]
 pub unsafe extern "C" fn close(fd: c_int) -> c_int {
-    // TODO: Implement close syscall
-    0
+    or_fail(fdio::close(fd).map(|()| 0), -1)
 }
 
 /// Duplicate a file descriptor
 #[no_mangle]
 pub unsafe extern "C" fn dup(fd: c_int) -> c_int {
-    // TODO: Implement dup syscall
-    -1
+    or_fail(fdio::dup(fd, 0, false), -1)
 }
 
 /// Duplicate a file descriptor to a specific fd
 #[no_mangle]
 pub unsafe extern "C" fn dup2(oldfd: c_int, newfd: c_int) -> c_int {
-    // TODO: Implement dup2 syscall
-    -1
+    or_fail(fdio::dup2(oldfd, newfd), -1)
+}
+
+/// Create a pipe: `fds[0]` reads what `fds[1]` writes
+#[no_mangle]
+pub unsafe extern "C" fn pipe(fds: *mut c_int) -> c_int {
+    pipe2(fds, 0)
+}
+
+/// Create a pipe with `O_CLOEXEC` or `O_NONBLOCK`
+#[no_mangle]
+pub unsafe extern "C" fn pipe2(fds: *mut c_int, flags: c_int) -> c_int {
+    if fds.is_null() {
+        set_errno(EINVAL);
+        return -1;
+    }
+
+    let result = fdio::pipe(flags & O_CLOEXEC != 0).and_then(|pipe| {
+        for fd in pipe {
+            fdio::set_status_flags(fd, flags)?;
+        }
+        Ok(pipe)
+    });
+    match result {
+        Ok(pipe) => {
+            *fds = pipe[0];
+            *fds.add(1) = pipe[1];
+            0
+        }
+        Err(errno) => {
+            set_errno(errno);
+            -1
+        }
+    }
 }
 
 /// Get process ID
@@ -232,10 +270,21 @@ pub extern "C" fn _exit(status: c_int) -> ! {
 }
 
 /// Check if file descriptor is a terminal
+///
+/// Only the debug console counts as one.
 #[no_mangle]
 pub extern "C" fn isatty(fd: c_int) -> c_int {
-    // TODO: Implement isatty
-    0
+    match fdio::is_console(fd) {
+        Ok(true) => 1,
+        Ok(false) => {
+            set_errno(ENOTTY);
+            0
+        }
+        Err(errno) => {
+            set_errno(errno);
+            0
+        }
+    }
 }
 
 /// Get environment variable
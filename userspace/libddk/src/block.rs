@@ -0,0 +1,669 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Block Device Class
+//!
+//! Block drivers implement `BlockDevice`. Clients reach them through the
+//! block protocol: opening the device returns a `BlockInfo` record, an
+//! I/O VMO and a FIFO. The client places data in the VMO and queues
+//! `BlockFifoRequest` records naming a VMO offset and a block range; the
+//! server answers each with a `BlockFifoResponse` carrying the same
+//! `reqid`. `BlockClient` wraps the client side back into a
+//! `BlockDevice`.
+//!
+//! Drives that report their own identity and health (NVMe and ATA
+//! SMART) answer `block_op::HEALTH` with a `DeviceHealth` record.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use libipc::Fifo;
+use libsys::{Error, Handle, Result, Rights, Status, Vmo};
+
+use crate::host::Connection;
+use crate::io_buffer::IoBuffer;
+
+/// Block device geometry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// Bytes per block
+    pub block_size: u32,
+
+    /// Largest transfer the device accepts, in bytes (0 = no limit)
+    pub max_transfer: u32,
+
+    /// Number of blocks
+    pub block_count: u64,
+}
+
+impl BlockInfo {
+    /// Size of the encoded record
+    pub const SIZE: usize = 16;
+
+    /// Encode as the `Open` reply body
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..4].copy_from_slice(&self.block_size.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.max_transfer.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.block_count.to_le_bytes());
+        buf
+    }
+
+    /// Decode an `Open` reply body
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE {
+            return None;
+        }
+        Some(Self {
+            block_size: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            max_transfer: u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            block_count: u64::from_le_bytes([
+                buf[8], buf[9], buf[10], buf[11], buf[12], buf[13], buf[14], buf[15],
+            ]),
+        })
+    }
+
+    /// Device size in bytes
+    pub fn size(&self) -> u64 {
+        self.block_count * self.block_size as u64
+    }
+}
+
+/// A block device
+///
+/// Buffers are whole blocks; `lba` counts blocks of `info().block_size`.
+pub trait BlockDevice {
+    /// Device geometry
+    fn info(&self) -> BlockInfo;
+
+    /// Read `buf.len() / block_size` blocks starting at `lba`
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()>;
+
+    /// Write `buf.len() / block_size` blocks starting at `lba`
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()>;
+
+    /// Make completed writes durable
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Identity and health as reported by the drive
+    ///
+    /// Fails with `NotSupported` for devices that don't report any.
+    fn health(&mut self) -> Result<DeviceHealth> {
+        Err(Error::new(Status::NotSupported))
+    }
+}
+
+/// Check that a transfer of `len` bytes at `lba` fits the device
+pub fn check_range(info: &BlockInfo, lba: u64, len: usize) -> Result<u64> {
+    let block_size = info.block_size as usize;
+    if block_size == 0 || len % block_size != 0 {
+        return Err(Error::new(Status::InvalidArgs));
+    }
+    let blocks = (len / block_size) as u64;
+    match lba.checked_add(blocks) {
+        Some(end) if end <= info.block_count => Ok(blocks),
+        _ => Err(Error::new(Status::InvalidArgs)),
+    }
+}
+
+/// ============================================================================
+/// Device Health
+/// ============================================================================
+
+/// `DeviceHealth::warnings` bits (the NVMe critical warning bits)
+pub mod health_warning {
+    /// Spare capacity is below the drive's threshold
+    pub const SPARE: u8 = 1 << 0;
+
+    /// Temperature is outside the drive's limits
+    pub const TEMPERATURE: u8 = 1 << 1;
+
+    /// Reliability is degraded; ATA drives report a SMART attribute past
+    /// its threshold this way
+    pub const RELIABILITY: u8 = 1 << 2;
+
+    /// The drive has made itself read-only
+    pub const READ_ONLY: u8 = 1 << 3;
+
+    /// The volatile memory backup has failed
+    pub const BACKUP: u8 = 1 << 4;
+}
+
+/// Identity and health of a drive
+///
+/// Text fields are space-padded ASCII. Values the drive doesn't report
+/// are `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceHealth {
+    /// Model number
+    pub model: [u8; 40],
+
+    /// Serial number
+    pub serial: [u8; 20],
+
+    /// Firmware revision
+    pub firmware: [u8; 8],
+
+    /// `health_warning` bits
+    pub warnings: u8,
+
+    /// Temperature in degrees Celsius
+    pub temperature: Option<i16>,
+
+    /// Share of the rated endurance used, in percent; may pass 100
+    pub percentage_used: Option<u8>,
+
+    /// Spare capacity left, in percent
+    pub available_spare: Option<u8>,
+
+    /// Hours spent powered on
+    pub power_on_hours: Option<u64>,
+
+    /// Number of power cycles
+    pub power_cycles: Option<u64>,
+
+    /// Power losses without a clean shutdown
+    pub unsafe_shutdowns: Option<u64>,
+
+    /// Unrecovered data errors
+    pub media_errors: Option<u64>,
+
+    /// Sectors remapped to spares
+    pub reallocated_sectors: Option<u64>,
+
+    /// Bytes read by the host
+    pub bytes_read: Option<u64>,
+
+    /// Bytes written by the host
+    pub bytes_written: Option<u64>,
+}
+
+impl DeviceHealth {
+    /// Size of the encoded record
+    pub const SIZE: usize = 136;
+
+    /// Offset of the counters in the encoded record
+    const COUNTERS: usize = 80;
+
+    /// Blank identity, no warnings and nothing reported
+    pub fn new() -> Self {
+        Self {
+            model: [b' '; 40],
+            serial: [b' '; 20],
+            firmware: [b' '; 8],
+            warnings: 0,
+            temperature: None,
+            percentage_used: None,
+            available_spare: None,
+            power_on_hours: None,
+            power_cycles: None,
+            unsafe_shutdowns: None,
+            media_errors: None,
+            reallocated_sectors: None,
+            bytes_read: None,
+            bytes_written: None,
+        }
+    }
+
+    /// Whether the drive reports neither a failure nor a pending one
+    pub fn healthy(&self) -> bool {
+        self.warnings == 0
+    }
+
+    /// Model number without padding
+    pub fn model_name(&self) -> &str {
+        text(&self.model)
+    }
+
+    /// Serial number without padding
+    pub fn serial_number(&self) -> &str {
+        text(&self.serial)
+    }
+
+    /// Firmware revision without padding
+    pub fn firmware_revision(&self) -> &str {
+        text(&self.firmware)
+    }
+
+    fn counters(&self) -> [Option<u64>; 7] {
+        [
+            self.power_on_hours,
+            self.power_cycles,
+            self.unsafe_shutdowns,
+            self.media_errors,
+            self.reallocated_sectors,
+            self.bytes_read,
+            self.bytes_written,
+        ]
+    }
+
+    /// Encode for the I/O VMO
+    ///
+    /// A presence mask at offset 74 says which optional values are set:
+    /// bits 0-2 for temperature, percentage used and available spare,
+    /// bits 3 and up for the counters at offset 80 in field order.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..40].copy_from_slice(&self.model);
+        buf[40..60].copy_from_slice(&self.serial);
+        buf[60..68].copy_from_slice(&self.firmware);
+        buf[68] = self.warnings;
+        buf[69] = self.percentage_used.unwrap_or(0);
+        buf[70] = self.available_spare.unwrap_or(0);
+        buf[72..74].copy_from_slice(&self.temperature.unwrap_or(0).to_le_bytes());
+
+        let mut present = self.temperature.is_some() as u16
+            | (self.percentage_used.is_some() as u16) << 1
+            | (self.available_spare.is_some() as u16) << 2;
+        for (i, counter) in self.counters().iter().enumerate() {
+            if let Some(value) = counter {
+                let offset = Self::COUNTERS + i * 8;
+                buf[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+                present |= 1 << (3 + i);
+            }
+        }
+        buf[74..76].copy_from_slice(&present.to_le_bytes());
+        buf
+    }
+
+    /// Decode a record written by `to_bytes`
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE {
+            return None;
+        }
+        let present = u16::from_le_bytes([buf[74], buf[75]]);
+        let counter = |i: usize| {
+            let offset = Self::COUNTERS + i * 8;
+            let value = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+            (present & 1 << (3 + i) != 0).then_some(value)
+        };
+
+        let mut health = Self::new();
+        health.model.copy_from_slice(&buf[0..40]);
+        health.serial.copy_from_slice(&buf[40..60]);
+        health.firmware.copy_from_slice(&buf[60..68]);
+        health.warnings = buf[68];
+        health.percentage_used = (present & 1 << 1 != 0).then_some(buf[69]);
+        health.available_spare = (present & 1 << 2 != 0).then_some(buf[70]);
+        health.temperature = (present & 1 != 0).then_some(i16::from_le_bytes([buf[72], buf[73]]));
+        health.power_on_hours = counter(0);
+        health.power_cycles = counter(1);
+        health.unsafe_shutdowns = counter(2);
+        health.media_errors = counter(3);
+        health.reallocated_sectors = counter(4);
+        health.bytes_read = counter(5);
+        health.bytes_written = counter(6);
+        Some(health)
+    }
+}
+
+impl Default for DeviceHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ASCII text of a padded identity field
+fn text(field: &[u8]) -> &str {
+    let end = field
+        .iter()
+        .rposition(|&b| b != b' ' && b != 0)
+        .map_or(0, |i| i + 1);
+    core::str::from_utf8(&field[..end]).unwrap_or("")
+}
+
+/// ============================================================================
+/// FIFO Protocol
+/// ============================================================================
+
+/// Block request opcodes
+pub mod block_op {
+    /// Read blocks into the I/O VMO
+    pub const READ: u32 = 1;
+
+    /// Write blocks from the I/O VMO
+    pub const WRITE: u32 = 2;
+
+    /// Flush the device's write cache
+    pub const FLUSH: u32 = 3;
+
+    /// Write the device's `DeviceHealth` record into the I/O VMO
+    pub const HEALTH: u32 = 4;
+}
+
+/// Number of records each direction of a block FIFO holds
+pub const BLOCK_FIFO_DEPTH: usize = 64;
+
+/// Request record sent by the client
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockFifoRequest {
+    /// `block_op` value
+    pub opcode: u32,
+
+    /// Client-chosen ID echoed in the response
+    pub reqid: u32,
+
+    /// Number of blocks
+    pub length: u32,
+
+    /// Reserved, must be 0
+    pub reserved: u32,
+
+    /// Byte offset of the data in the I/O VMO
+    pub vmo_offset: u64,
+
+    /// First block
+    pub lba: u64,
+}
+
+/// Response record sent by the server
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockFifoResponse {
+    /// 0 on success, otherwise a negated `Status`
+    pub status: i32,
+
+    /// `reqid` of the request
+    pub reqid: u32,
+
+    /// Number of blocks transferred
+    pub count: u32,
+
+    /// Reserved
+    pub reserved: u32,
+
+    /// Reserved
+    pub reserved2: [u64; 2],
+}
+
+/// Serves the block protocol for one client
+pub struct BlockServer {
+    fifo: Fifo,
+    io: IoBuffer,
+}
+
+impl BlockServer {
+    /// Create a server with an I/O VMO of `io_size` bytes
+    ///
+    /// Returns the server and the connection to hand to the client:
+    /// `info` is the encoded `BlockInfo`, `handles` are the I/O VMO and
+    /// the client end of the FIFO.
+    pub fn create(info: &BlockInfo, io_size: usize) -> Result<(Self, Connection)> {
+        let io = IoBuffer::create(io_size, "block-io")?;
+        let (server_end, client_end) =
+            Fifo::create(BLOCK_FIFO_DEPTH, core::mem::size_of::<BlockFifoRequest>())?;
+
+        let mut handles: Vec<Handle> = Vec::new();
+        handles.push(io.vmo().handle().duplicate(Rights::SAME_RIGHTS)?);
+        handles.push(client_end.into_handle());
+
+        let connection = Connection {
+            info: info.to_bytes().to_vec(),
+            handles,
+        };
+        Ok((Self { fifo: server_end, io }, connection))
+    }
+
+    /// Execute one request against `device`
+    fn execute(&mut self, device: &mut dyn BlockDevice, req: &BlockFifoRequest) -> Result<u32> {
+        let info = device.info();
+        let len = req.length as usize * info.block_size as usize;
+
+        match req.opcode {
+            block_op::READ | block_op::WRITE => {
+                check_range(&info, req.lba, len)?;
+                let buf = self
+                    .io
+                    .slice_mut(req.vmo_offset as usize, len)
+                    .ok_or(Error::new(Status::InvalidArgs))?;
+                if req.opcode == block_op::READ {
+                    device.read_blocks(req.lba, buf)?;
+                } else {
+                    device.write_blocks(req.lba, buf)?;
+                }
+                Ok(req.length)
+            }
+            block_op::FLUSH => device.flush().map(|()| 0),
+            block_op::HEALTH => {
+                let health = device.health()?;
+                let buf = self
+                    .io
+                    .slice_mut(req.vmo_offset as usize, DeviceHealth::SIZE)
+                    .ok_or(Error::new(Status::InvalidArgs))?;
+                buf.copy_from_slice(&health.to_bytes());
+                Ok(0)
+            }
+            _ => Err(Error::new(Status::NotSupported)),
+        }
+    }
+
+    /// Handle queued requests
+    ///
+    /// Returns true if any request was processed.
+    pub fn poll(&mut self, device: &mut dyn BlockDevice) -> Result<bool> {
+        let mut requests = [BlockFifoRequest::default(); 8];
+        let count = self.fifo.read(&mut requests)?;
+
+        for req in &requests[..count] {
+            let response = match self.execute(device, req) {
+                Ok(blocks) => BlockFifoResponse {
+                    reqid: req.reqid,
+                    count: blocks,
+                    ..Default::default()
+                },
+                Err(err) => BlockFifoResponse {
+                    status: -err.status().into_raw(),
+                    reqid: req.reqid,
+                    ..Default::default()
+                },
+            };
+            // The client sized its queue to the FIFO depth, so a full FIFO
+            // means it stopped reading responses
+            if self.fifo.write(core::slice::from_ref(&response))? == 0 {
+                return Err(Error::new(Status::BadState));
+            }
+        }
+
+        Ok(count > 0)
+    }
+}
+
+/// Client side of the block protocol
+///
+/// Requests are issued one at a time and waited for by polling the FIFO.
+/// A client sharing a thread with its server installs an idle hook that
+/// polls the server while waiting.
+pub struct BlockClient {
+    info: BlockInfo,
+    fifo: Fifo,
+    io: IoBuffer,
+    next_reqid: u32,
+    idle: Option<Box<dyn FnMut()>>,
+}
+
+impl BlockClient {
+    /// Connect using the body and handles of an `Open` reply
+    pub fn new(body: &[u8], handles: Vec<Handle>) -> Result<Self> {
+        let info = BlockInfo::from_bytes(body).ok_or(Error::new(Status::InvalidArgs))?;
+        let [vmo, fifo] = <[Handle; 2]>::try_from(handles).map_err(|_| Error::new(Status::InvalidArgs))?;
+        if info.block_size == 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let vmo = unsafe { Vmo::from_handle(vmo) };
+        let size = vmo.get_size()? as usize;
+        let io = IoBuffer::map(vmo, size)?;
+        let fifo = unsafe { Fifo::from_handle(fifo) };
+        Ok(Self {
+            info,
+            fifo,
+            io,
+            next_reqid: 1,
+            idle: None,
+        })
+    }
+
+    /// Run `hook` whenever the client waits for the server
+    pub fn set_idle(&mut self, hook: Box<dyn FnMut()>) {
+        self.idle = Some(hook);
+    }
+
+    /// Largest byte count a single request may move
+    fn chunk_size(&self) -> usize {
+        let block_size = self.info.block_size as usize;
+        let mut limit = self.io.size();
+        if self.info.max_transfer != 0 {
+            limit = limit.min(self.info.max_transfer as usize);
+        }
+        limit / block_size * block_size
+    }
+
+    /// Issue one request and wait for its response
+    fn transact(&mut self, opcode: u32, lba: u64, blocks: u32) -> Result<()> {
+        let reqid = self.next_reqid;
+        self.next_reqid = self.next_reqid.wrapping_add(1);
+        let request = BlockFifoRequest {
+            opcode,
+            reqid,
+            length: blocks,
+            reserved: 0,
+            vmo_offset: 0,
+            lba,
+        };
+        while self.fifo.write(core::slice::from_ref(&request))? == 0 {
+            self.wait();
+        }
+
+        let mut response = BlockFifoResponse::default();
+        loop {
+            if self.fifo.read(core::slice::from_mut(&mut response))? == 1 {
+                if response.reqid == reqid {
+                    break;
+                }
+                // Stale response from an abandoned request
+                continue;
+            }
+            self.wait();
+        }
+
+        if response.status != 0 {
+            return Err(Error::from_raw(-response.status));
+        }
+        Ok(())
+    }
+
+    fn wait(&mut self) {
+        match self.idle.as_mut() {
+            Some(hook) => hook(),
+            None => core::hint::spin_loop(),
+        }
+    }
+}
+
+impl BlockDevice for BlockClient {
+    fn info(&self) -> BlockInfo {
+        self.info
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let chunk_size = self.chunk_size();
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(chunk_size) {
+            let blocks = (chunk.len() / self.info.block_size as usize) as u32;
+            self.transact(block_op::READ, lba, blocks)?;
+            let data = self.io.slice(0, chunk.len()).ok_or(Error::new(Status::Internal))?;
+            chunk.copy_from_slice(data);
+            lba += blocks as u64;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        check_range(&self.info, lba, buf.len())?;
+        let chunk_size = self.chunk_size();
+        let mut lba = lba;
+        for chunk in buf.chunks(chunk_size) {
+            let blocks = (chunk.len() / self.info.block_size as usize) as u32;
+            let data = self.io.slice_mut(0, chunk.len()).ok_or(Error::new(Status::Internal))?;
+            data.copy_from_slice(chunk);
+            self.transact(block_op::WRITE, lba, blocks)?;
+            lba += blocks as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.transact(block_op::FLUSH, 0, 0)
+    }
+
+    fn health(&mut self) -> Result<DeviceHealth> {
+        self.transact(block_op::HEALTH, 0, 0)?;
+        let data = self.io.slice(0, DeviceHealth::SIZE).ok_or(Error::new(Status::Internal))?;
+        DeviceHealth::from_bytes(data).ok_or(Error::new(Status::Internal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_info_roundtrip() {
+        let info = BlockInfo {
+            block_size: 512,
+            max_transfer: 65536,
+            block_count: 0x1_0000_0000,
+        };
+        assert_eq!(BlockInfo::from_bytes(&info.to_bytes()), Some(info));
+        assert_eq!(BlockInfo::from_bytes(&[0u8; 8]), None);
+        assert_eq!(info.size(), 0x200_0000_0000);
+    }
+
+    #[test]
+    fn test_check_range() {
+        let info = BlockInfo {
+            block_size: 512,
+            max_transfer: 0,
+            block_count: 100,
+        };
+        assert_eq!(check_range(&info, 0, 1024).unwrap(), 2);
+        assert_eq!(check_range(&info, 98, 1024).unwrap(), 2);
+        assert!(check_range(&info, 99, 1024).is_err());
+        assert!(check_range(&info, 0, 100).is_err());
+        assert!(check_range(&info, u64::MAX, 512).is_err());
+    }
+
+    #[test]
+    fn test_device_health_roundtrip() {
+        let mut health = DeviceHealth::new();
+        health.model[..9].copy_from_slice(b"QEMU NVMe");
+        health.serial[..4].copy_from_slice(b"1234");
+        health.warnings = health_warning::TEMPERATURE;
+        health.temperature = Some(-5);
+        health.available_spare = Some(100);
+        health.power_cycles = Some(7);
+        health.bytes_written = Some(1 << 40);
+
+        let decoded = DeviceHealth::from_bytes(&health.to_bytes()).unwrap();
+        assert_eq!(decoded, health);
+        assert_eq!(decoded.model_name(), "QEMU NVMe");
+        assert_eq!(decoded.serial_number(), "1234");
+        assert_eq!(decoded.firmware_revision(), "");
+        assert_eq!(decoded.percentage_used, None);
+        assert_eq!(decoded.power_on_hours, None);
+        assert!(!decoded.healthy());
+        assert_eq!(DeviceHealth::from_bytes(&[0u8; 100]), None);
+    }
+
+    #[test]
+    fn test_fifo_record_sizes() {
+        assert_eq!(core::mem::size_of::<BlockFifoRequest>(), 32);
+        assert_eq!(core::mem::size_of::<BlockFifoResponse>(), 32);
+    }
+}
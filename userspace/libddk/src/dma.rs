@@ -0,0 +1,314 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! DMA Memory
+//!
+//! Devices address memory through a bus transaction initiator (BTI).
+//! Pinning a VMO through a BTI commits its pages, keeps them resident and
+//! returns the device-visible addresses; the returned PMT (pinned memory
+//! token) must be unpinned before the pages can be reused.
+//!
+//! A BTI created on an `Iommu` has its own IO address space, so the
+//! device can only reach what has been pinned for it.
+//!
+//! `DmaBuffer` bundles the common case: a physically contiguous VMO,
+//! pinned as one range and mapped for CPU access.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use libsys::syscall::{syscall1, syscall4, syscall5, syscall6, SyscallNumber};
+use libsys::{vmar, Error, Handle, Result, Rights, Status, Vmo};
+
+use crate::check;
+use crate::mmio::map_flags;
+
+/// Pin permissions (mirror the kernel's `bti_perm`)
+pub mod bti_perm {
+    /// Device may read the pages
+    pub const READ: u32 = 0x01;
+
+    /// Device may write the pages
+    pub const WRITE: u32 = 0x02;
+
+    /// Return a single address for a contiguous VMO
+    pub const CONTIGUOUS: u32 = 0x10;
+}
+
+/// IOMMU kinds (mirror the kernel's `iommu_type`)
+pub mod iommu_kind {
+    /// No translation: device addresses are physical addresses
+    pub const DUMMY: u32 = 0;
+
+    /// Intel VT-d
+    pub const INTEL: u32 = 1;
+
+    /// Arm SMMUv3
+    pub const ARM_SMMU: u32 = 2;
+}
+
+/// Page size used for pinning
+pub const PAGE_SIZE: usize = 4096;
+
+/// Output buffers for `BtiPin` (mirrors the kernel's `BtiPinBuffers`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BtiPinBuffers {
+    /// Pointer to an array of u64 device addresses
+    pub addrs: usize,
+
+    /// Number of entries in `addrs`
+    pub addrs_count: usize,
+
+    /// Pointer to store the PMT handle
+    pub pmt_out: usize,
+}
+
+/// IOMMU
+#[derive(Debug)]
+pub struct Iommu {
+    handle: Handle,
+}
+
+impl Iommu {
+    /// Create an IOMMU object of `kind` (see `iommu_kind`)
+    ///
+    /// The kernel finds the hardware from the firmware tables. Creating
+    /// the first Intel or SMMU object turns translation on, after which
+    /// devices without a BTI on it can no longer reach memory.
+    pub fn create(resource: &Handle, kind: u32) -> Result<Self> {
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall5(
+                SyscallNumber::IommuCreate as u64,
+                resource.raw() as u64,
+                kind as u64,
+                0, // desc
+                0, // desc_size
+                &mut out as *mut u32 as u64,
+            ))?;
+            Ok(Self {
+                handle: Handle::from_raw(out, Rights::all()),
+            })
+        }
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+}
+
+/// Bus transaction initiator
+#[derive(Debug)]
+pub struct Bti {
+    handle: Handle,
+}
+
+impl Bti {
+    /// Create a BTI for the device identified by `bti_id` without an IOMMU
+    ///
+    /// The kernel hands out physical addresses. This fails once a hardware
+    /// IOMMU is enabled; use `create_on` then.
+    pub fn create(bti_id: u64) -> Result<Self> {
+        Self::create_raw(0, bti_id)
+    }
+
+    /// Create a BTI on `iommu` for the device identified by `bti_id`
+    ///
+    /// For PCI devices `bti_id` is the segment in bits 16-31 and the
+    /// bus, device and function (the requester ID) in bits 0-15.
+    pub fn create_on(iommu: &Iommu, bti_id: u64) -> Result<Self> {
+        Self::create_raw(iommu.handle().raw(), bti_id)
+    }
+
+    fn create_raw(iommu: u32, bti_id: u64) -> Result<Self> {
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall4(
+                SyscallNumber::BtiCreate as u64,
+                iommu as u64,
+                0, // options
+                bti_id,
+                &mut out as *mut u32 as u64,
+            ))?;
+            Ok(Self {
+                handle: Handle::from_raw(out, Rights::all()),
+            })
+        }
+    }
+
+    /// Unmap pins that were dropped without being unpinned
+    ///
+    /// Call this only after resetting the device, so that it can no
+    /// longer be targeting them.
+    pub fn release_quarantine(&self) -> Result<()> {
+        unsafe {
+            check(syscall1(
+                SyscallNumber::BtiReleaseQuarantine as u64,
+                self.handle.raw() as u64,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Pin `size` bytes of `vmo` at `offset`
+    ///
+    /// Returns the PMT and one device address per page, or a single
+    /// address if `bti_perm::CONTIGUOUS` is set.
+    pub fn pin(&self, vmo: &Vmo, offset: u64, size: usize, perms: u32) -> Result<(Pmt, Vec<u64>)> {
+        if size == 0 || size % PAGE_SIZE != 0 || offset as usize % PAGE_SIZE != 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let count = if perms & bti_perm::CONTIGUOUS != 0 {
+            1
+        } else {
+            size / PAGE_SIZE
+        };
+        let mut addrs = vec![0u64; count];
+        let mut pmt: u32 = 0;
+        let buffers = BtiPinBuffers {
+            addrs: addrs.as_mut_ptr() as usize,
+            addrs_count: count,
+            pmt_out: &mut pmt as *mut u32 as usize,
+        };
+
+        unsafe {
+            check(syscall6(
+                SyscallNumber::BtiPin as u64,
+                self.handle.raw() as u64,
+                perms as u64,
+                vmo.handle().raw() as u64,
+                offset,
+                size as u64,
+                &buffers as *const BtiPinBuffers as u64,
+            ))?;
+            Ok((Pmt { handle: Handle::from_raw(pmt, Rights::all()) }, addrs))
+        }
+    }
+}
+
+/// Pinned memory token
+///
+/// Closing the handle without `unpin` leaves the pages pinned in the
+/// BTI's quarantine and makes the kernel log a warning.
+#[derive(Debug)]
+pub struct Pmt {
+    handle: Handle,
+}
+
+impl Pmt {
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Unpin the pages
+    ///
+    /// The device must no longer access them.
+    pub fn unpin(self) -> Result<()> {
+        unsafe {
+            check(syscall1(SyscallNumber::PmtUnpin as u64, self.handle.raw() as u64))?;
+        }
+        Ok(())
+    }
+}
+
+/// Contiguous, pinned and mapped DMA buffer
+pub struct DmaBuffer {
+    vmo: Vmo,
+    pmt: Pmt,
+    phys: u64,
+    vaddr: usize,
+    size: usize,
+}
+
+impl DmaBuffer {
+    /// Allocate a page-aligned contiguous buffer of at least `size` bytes
+    pub fn create(bti: &Bti, size: usize) -> Result<Self> {
+        Self::create_aligned(bti, size, 0)
+    }
+
+    /// Allocate a contiguous buffer aligned to `1 << alignment_log2`
+    ///
+    /// An alignment of 0 means page alignment.
+    pub fn create_aligned(bti: &Bti, size: usize, alignment_log2: u32) -> Result<Self> {
+        if size == 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+        let mut out: u32 = 0;
+        let vmo = unsafe {
+            check(syscall4(
+                SyscallNumber::VmoCreateContiguous as u64,
+                bti.handle().raw() as u64,
+                size as u64,
+                alignment_log2 as u64,
+                &mut out as *mut u32 as u64,
+            ))?;
+            Vmo::from_handle(Handle::from_raw(out, Rights::all()))
+        };
+
+        let perms = bti_perm::READ | bti_perm::WRITE | bti_perm::CONTIGUOUS;
+        let (pmt, addrs) = bti.pin(&vmo, 0, size, perms)?;
+
+        let root = vmar::root_self()?;
+        let vaddr = vmar::map(&root, 0, &vmo, 0, size, map_flags::PERM_READ | map_flags::PERM_WRITE)?;
+
+        Ok(Self {
+            vmo,
+            pmt,
+            phys: addrs[0],
+            vaddr,
+            size,
+        })
+    }
+
+    /// Device-visible address of the first byte
+    pub fn phys(&self) -> u64 {
+        self.phys
+    }
+
+    /// CPU address of the first byte
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.vaddr as *mut u8
+    }
+
+    /// Size in bytes (a multiple of the page size)
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Backing VMO
+    pub fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// CPU view of the buffer
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.as_ptr(), self.size) }
+    }
+
+    /// Mutable CPU view of the buffer
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_ptr(), self.size) }
+    }
+
+    /// Unpin, unmap and free the buffer
+    pub fn release(self) -> Result<()> {
+        self.pmt.unpin()?;
+        let root = vmar::root_self()?;
+        vmar::unmap(&root, self.vaddr, self.size)?;
+        self.vmo.into_handle().close()
+    }
+}
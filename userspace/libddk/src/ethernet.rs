@@ -0,0 +1,363 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ethernet Device Class
+//!
+//! Ethernet drivers implement `EthernetDevice`. Opening the device
+//! returns an `EthernetInfo` record, an I/O VMO and two FIFOs of
+//! `EthFifoEntry` records, each naming a frame buffer inside the VMO:
+//!
+//! - **TX**: the client writes a frame into the VMO and queues its entry;
+//!   the server sends it and returns the entry with `TX_OK` set
+//! - **RX**: the client queues empty buffers; the server fills one per
+//!   received frame and returns it with `RX_OK` set and `length` updated
+//!
+//! `EthernetClient` wraps the client side back into an `EthernetDevice`.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use libipc::Fifo;
+use libsys::{Error, Handle, Result, Rights, Status, Vmo};
+
+use crate::host::Connection;
+use crate::io_buffer::IoBuffer;
+
+/// Ethernet device parameters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EthernetInfo {
+    /// Station MAC address
+    pub mac: [u8; 6],
+
+    /// Largest payload, excluding the 14-byte ethernet header
+    pub mtu: u16,
+}
+
+impl EthernetInfo {
+    /// Size of the encoded record
+    pub const SIZE: usize = 8;
+
+    /// Encode as the `Open` reply body
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..6].copy_from_slice(&self.mac);
+        buf[6..8].copy_from_slice(&self.mtu.to_le_bytes());
+        buf
+    }
+
+    /// Decode an `Open` reply body
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&buf[0..6]);
+        Some(Self {
+            mac,
+            mtu: u16::from_le_bytes([buf[6], buf[7]]),
+        })
+    }
+}
+
+/// An ethernet device
+pub trait EthernetDevice {
+    /// Device parameters
+    fn info(&self) -> EthernetInfo;
+
+    /// Queue one frame for transmission
+    fn send(&mut self, frame: &[u8]) -> Result<()>;
+
+    /// Copy the next received frame into `buf`
+    ///
+    /// Returns the frame length, or 0 if nothing has arrived.
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize>;
+}
+
+/// ============================================================================
+/// FIFO Protocol
+/// ============================================================================
+
+/// Entry flags
+pub mod eth_flags {
+    /// Frame received into the buffer
+    pub const RX_OK: u16 = 0x0001;
+
+    /// Frame transmitted
+    pub const TX_OK: u16 = 0x0001;
+}
+
+/// Number of entries each FIFO direction holds
+pub const ETH_FIFO_DEPTH: usize = 256;
+
+/// Frame buffer size used by `EthernetClient`
+pub const ETH_BUFFER_SIZE: usize = 2048;
+
+/// Frame buffer descriptor
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EthFifoEntry {
+    /// Byte offset of the buffer in the I/O VMO
+    pub offset: u32,
+
+    /// Buffer size (RX request) or frame length (TX, RX completion)
+    pub length: u16,
+
+    /// `eth_flags` bits, set by the server
+    pub flags: u16,
+
+    /// Client cookie, returned unchanged
+    pub cookie: u64,
+}
+
+/// Serves the ethernet protocol for one client
+pub struct EthernetServer {
+    tx: Fifo,
+    rx: Fifo,
+    io: IoBuffer,
+
+    /// Empty RX buffers queued by the client
+    rx_free: VecDeque<EthFifoEntry>,
+}
+
+impl EthernetServer {
+    /// Create a server with an I/O VMO of `io_size` bytes
+    ///
+    /// `handles` in the returned connection are the I/O VMO, the TX FIFO
+    /// and the RX FIFO.
+    pub fn create(info: &EthernetInfo, io_size: usize) -> Result<(Self, Connection)> {
+        let io = IoBuffer::create(io_size, "eth-io")?;
+        let entry_size = core::mem::size_of::<EthFifoEntry>();
+        let (tx, tx_client) = Fifo::create(ETH_FIFO_DEPTH, entry_size)?;
+        let (rx, rx_client) = Fifo::create(ETH_FIFO_DEPTH, entry_size)?;
+
+        let mut handles: Vec<Handle> = Vec::new();
+        handles.push(io.vmo().handle().duplicate(Rights::SAME_RIGHTS)?);
+        handles.push(tx_client.into_handle());
+        handles.push(rx_client.into_handle());
+
+        let connection = Connection {
+            info: info.to_bytes().to_vec(),
+            handles,
+        };
+        let server = Self {
+            tx,
+            rx,
+            io,
+            rx_free: VecDeque::new(),
+        };
+        Ok((server, connection))
+    }
+
+    /// Move frames between the client and `device`
+    ///
+    /// Returns true if any frame moved.
+    pub fn poll(&mut self, device: &mut dyn EthernetDevice) -> Result<bool> {
+        let mut progress = false;
+        let mut entries = [EthFifoEntry::default(); 16];
+
+        // Transmit
+        let count = self.tx.read(&mut entries)?;
+        for entry in &mut entries[..count] {
+            entry.flags = match self.io.slice(entry.offset as usize, entry.length as usize) {
+                Some(frame) if device.send(frame).is_ok() => eth_flags::TX_OK,
+                _ => 0,
+            };
+        }
+        if count > 0 {
+            self.tx.write(&entries[..count])?;
+            progress = true;
+        }
+
+        // Collect empty receive buffers
+        let count = self.rx.read(&mut entries)?;
+        self.rx_free.extend(entries[..count].iter().copied());
+
+        // Receive
+        while let Some(mut entry) = self.rx_free.pop_front() {
+            let buf = match self.io.slice_mut(entry.offset as usize, entry.length as usize) {
+                Some(buf) => buf,
+                None => {
+                    // Return bad buffers unfilled
+                    entry.flags = 0;
+                    self.rx.write(core::slice::from_ref(&entry))?;
+                    continue;
+                }
+            };
+            let len = device.recv(buf)?;
+            if len == 0 {
+                self.rx_free.push_front(entry);
+                break;
+            }
+            entry.length = len as u16;
+            entry.flags = eth_flags::RX_OK;
+            self.rx.write(core::slice::from_ref(&entry))?;
+            progress = true;
+        }
+
+        Ok(progress)
+    }
+}
+
+/// Client side of the ethernet protocol
+///
+/// The I/O VMO is split into `ETH_BUFFER_SIZE` buffers: the first half
+/// transmits, the second half is kept queued for reception. A client
+/// sharing a thread with its server installs an idle hook that polls the
+/// server whenever the client runs out of work.
+pub struct EthernetClient {
+    info: EthernetInfo,
+    tx: Fifo,
+    rx: Fifo,
+    io: IoBuffer,
+
+    /// Offsets of transmit buffers not queued to the server
+    tx_free: Vec<u32>,
+
+    idle: Option<Box<dyn FnMut()>>,
+}
+
+impl EthernetClient {
+    /// Connect using the body and handles of an `Open` reply
+    pub fn new(body: &[u8], handles: Vec<Handle>) -> Result<Self> {
+        let info = EthernetInfo::from_bytes(body).ok_or(Error::new(Status::InvalidArgs))?;
+        let [vmo, tx, rx] = <[Handle; 3]>::try_from(handles).map_err(|_| Error::new(Status::InvalidArgs))?;
+        let vmo = unsafe { Vmo::from_handle(vmo) };
+        let size = vmo.get_size()? as usize;
+        let io = IoBuffer::map(vmo, size)?;
+        let tx = unsafe { Fifo::from_handle(tx) };
+        let rx = unsafe { Fifo::from_handle(rx) };
+
+        let buffers = (size / ETH_BUFFER_SIZE).min(ETH_FIFO_DEPTH * 2);
+        if buffers < 2 {
+            return Err(Error::new(Status::BufferTooSmall));
+        }
+        let tx_count = buffers / 2;
+        let tx_free = (0..tx_count).map(|i| (i * ETH_BUFFER_SIZE) as u32).collect();
+
+        let client = Self {
+            info,
+            tx,
+            rx,
+            io,
+            tx_free,
+            idle: None,
+        };
+        for i in tx_count..buffers {
+            client.queue_rx((i * ETH_BUFFER_SIZE) as u32)?;
+        }
+        Ok(client)
+    }
+
+    /// Run `hook` whenever the client has nothing to do
+    pub fn set_idle(&mut self, hook: Box<dyn FnMut()>) {
+        self.idle = Some(hook);
+    }
+
+    fn idle(&mut self) {
+        match self.idle.as_mut() {
+            Some(hook) => hook(),
+            None => core::hint::spin_loop(),
+        }
+    }
+
+    fn queue_rx(&self, offset: u32) -> Result<()> {
+        let entry = EthFifoEntry {
+            offset,
+            length: ETH_BUFFER_SIZE as u16,
+            flags: 0,
+            cookie: 0,
+        };
+        if self.rx.write(core::slice::from_ref(&entry))? == 0 {
+            return Err(Error::new(Status::BadState));
+        }
+        Ok(())
+    }
+
+    /// Return transmitted buffers to the free list
+    fn reclaim_tx(&mut self) -> Result<()> {
+        let mut entries = [EthFifoEntry::default(); 16];
+        loop {
+            let count = self.tx.read(&mut entries)?;
+            self.tx_free.extend(entries[..count].iter().map(|entry| entry.offset));
+            if count < entries.len() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl EthernetDevice for EthernetClient {
+    fn info(&self) -> EthernetInfo {
+        self.info
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<()> {
+        if frame.is_empty() || frame.len() > ETH_BUFFER_SIZE {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        self.reclaim_tx()?;
+        if self.tx_free.is_empty() {
+            self.idle();
+            self.reclaim_tx()?;
+        }
+        let offset = self.tx_free.pop().ok_or(Error::new(Status::Busy))?;
+
+        let buf = self
+            .io
+            .slice_mut(offset as usize, frame.len())
+            .ok_or(Error::new(Status::Internal))?;
+        buf.copy_from_slice(frame);
+        let entry = EthFifoEntry {
+            offset,
+            length: frame.len() as u16,
+            flags: 0,
+            cookie: 0,
+        };
+        if self.tx.write(core::slice::from_ref(&entry))? == 0 {
+            self.tx_free.push(offset);
+            return Err(Error::new(Status::Busy));
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut entry = EthFifoEntry::default();
+        if self.rx.read(core::slice::from_mut(&mut entry))? == 0 {
+            self.idle();
+            if self.rx.read(core::slice::from_mut(&mut entry))? == 0 {
+                return Ok(0);
+            }
+        }
+
+        let len = entry.length as usize;
+        let copied = match self.io.slice(entry.offset as usize, len) {
+            Some(frame) if entry.flags & eth_flags::RX_OK != 0 && len <= buf.len() => {
+                buf[..len].copy_from_slice(frame);
+                len
+            }
+            // Unfilled or oversized; drop it
+            _ => 0,
+        };
+        self.queue_rx(entry.offset)?;
+        Ok(copied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ethernet_info_roundtrip() {
+        let info = EthernetInfo {
+            mac: [0x52, 0x54, 0x00, 0x12, 0x34, 0x56],
+            mtu: 1500,
+        };
+        assert_eq!(EthernetInfo::from_bytes(&info.to_bytes()), Some(info));
+        assert_eq!(core::mem::size_of::<EthFifoEntry>(), 16);
+    }
+}
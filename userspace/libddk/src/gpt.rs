@@ -0,0 +1,423 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! GUID Partition Tables
+//!
+//! Reads the GPT of a block device (UEFI 2.x, chapter 5). The primary
+//! header lives in LBA 1 and the backup in the last LBA; the backup is
+//! used when the primary is missing or fails its checksum. Both the
+//! header and the partition entry array are CRC32 protected.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use libsys::{Error, Result, Status};
+
+use crate::block::BlockDevice;
+
+/// "EFI PART"
+pub const GPT_SIGNATURE: [u8; 8] = *b"EFI PART";
+
+/// Smallest header the specification allows
+const MIN_HEADER_SIZE: usize = 92;
+
+/// Smallest partition entry the specification allows
+const MIN_ENTRY_SIZE: usize = 128;
+
+/// Largest partition entry array this reader accepts
+const MAX_ENTRY_ARRAY_SIZE: usize = 1024 * 1024;
+
+/// Partition name length in UTF-16 code units
+const NAME_UNITS: usize = 36;
+
+/// CRC32 (IEEE 802.3, reflected) as used by GPT
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// A GUID in its on-disk (mixed-endian) byte order
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// Unused partition entry
+    pub const ZERO: Guid = Guid([0; 16]);
+
+    /// EFI system partition, C12A7328-F81F-11D2-BA4B-00A0C93EC93B
+    pub const EFI_SYSTEM: Guid = Guid([
+        0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b,
+    ]);
+
+    /// Basic data partition, EBD0A0A2-B9E5-4433-87C0-68B6B72699C7
+    pub const BASIC_DATA: Guid = Guid([
+        0xa2, 0xa0, 0xd0, 0xeb, 0xe5, 0xb9, 0x33, 0x44, 0x87, 0xc0, 0x68, 0xb6, 0xb7, 0x26, 0x99, 0xc7,
+    ]);
+
+    /// Linux filesystem data, 0FC63DAF-8483-4772-8E79-3D69D8477DE4
+    pub const LINUX_FS: Guid = Guid([
+        0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4,
+    ]);
+
+    /// Read a GUID from the first 16 bytes of `buf`
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&buf[..16]);
+        Guid(bytes)
+    }
+
+    /// Short name for well-known partition types
+    pub fn type_name(&self) -> Option<&'static str> {
+        match *self {
+            Guid::EFI_SYSTEM => Some("efi-system"),
+            Guid::BASIC_DATA => Some("basic-data"),
+            Guid::LINUX_FS => Some("linux-fs"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9]
+        )?;
+        for byte in &b[10..] {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// GPT header fields this reader uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GptHeader {
+    /// LBA holding this header
+    pub my_lba: u64,
+
+    /// LBA of the other copy of the header
+    pub alternate_lba: u64,
+
+    /// First block partitions may use
+    pub first_usable_lba: u64,
+
+    /// Last block partitions may use
+    pub last_usable_lba: u64,
+
+    pub disk_guid: Guid,
+
+    /// First block of the partition entry array
+    pub entry_lba: u64,
+
+    pub entry_count: u32,
+    pub entry_size: u32,
+    pub entry_array_crc32: u32,
+}
+
+impl GptHeader {
+    /// Parse and verify the header at the start of `block`
+    ///
+    /// Fails with `Status::NotFound` if there is no GPT signature and
+    /// `Status::IoError` if the header is corrupt.
+    pub fn parse(block: &[u8]) -> Result<Self> {
+        if block.len() < MIN_HEADER_SIZE || block[0..8] != GPT_SIGNATURE {
+            return Err(Error::new(Status::NotFound));
+        }
+        let header_size = get_u32(block, 12) as usize;
+        if header_size < MIN_HEADER_SIZE || header_size > block.len() {
+            return Err(Error::new(Status::IoError));
+        }
+
+        // The checksum covers the header with its own CRC field zeroed
+        let mut copy = Vec::from(&block[..header_size]);
+        copy[16..20].fill(0);
+        if crc32(&copy) != get_u32(block, 16) {
+            return Err(Error::new(Status::IoError));
+        }
+
+        let header = Self {
+            my_lba: get_u64(block, 24),
+            alternate_lba: get_u64(block, 32),
+            first_usable_lba: get_u64(block, 40),
+            last_usable_lba: get_u64(block, 48),
+            disk_guid: Guid::from_bytes(&block[56..72]),
+            entry_lba: get_u64(block, 72),
+            entry_count: get_u32(block, 80),
+            entry_size: get_u32(block, 84),
+            entry_array_crc32: get_u32(block, 88),
+        };
+        if (header.entry_size as usize) < MIN_ENTRY_SIZE
+            || header.entry_size % 8 != 0
+            || header.entry_array_size() > MAX_ENTRY_ARRAY_SIZE
+            || header.first_usable_lba > header.last_usable_lba
+        {
+            return Err(Error::new(Status::IoError));
+        }
+        Ok(header)
+    }
+
+    /// Size of the partition entry array in bytes
+    pub fn entry_array_size(&self) -> usize {
+        self.entry_count as usize * self.entry_size as usize
+    }
+}
+
+/// A used partition entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    /// Position in the entry array, starting at 1
+    pub index: u32,
+
+    pub type_guid: Guid,
+    pub guid: Guid,
+
+    /// First block
+    pub first_lba: u64,
+
+    /// Last block, inclusive
+    pub last_lba: u64,
+
+    pub attributes: u64,
+
+    /// Partition name
+    pub name: String,
+}
+
+impl Partition {
+    /// Parse entry `index` (1-based); `None` for unused entries
+    pub fn parse(entry: &[u8], index: u32) -> Option<Self> {
+        let type_guid = Guid::from_bytes(&entry[0..16]);
+        if type_guid == Guid::ZERO {
+            return None;
+        }
+
+        let units = (0..NAME_UNITS)
+            .map(|i| u16::from_le_bytes([entry[56 + 2 * i], entry[57 + 2 * i]]))
+            .take_while(|&unit| unit != 0);
+        let name = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+
+        Some(Self {
+            index,
+            type_guid,
+            guid: Guid::from_bytes(&entry[16..32]),
+            first_lba: get_u64(entry, 32),
+            last_lba: get_u64(entry, 40),
+            attributes: get_u64(entry, 48),
+            name,
+        })
+    }
+
+    /// Number of blocks
+    pub fn block_count(&self) -> u64 {
+        self.last_lba - self.first_lba + 1
+    }
+}
+
+/// A parsed partition table
+#[derive(Debug, Clone)]
+pub struct Gpt {
+    pub header: GptHeader,
+    pub partitions: Vec<Partition>,
+}
+
+/// Read the partition table of `device`
+///
+/// Partitions that fall outside the usable range or overlap the tables
+/// are skipped.
+pub fn read_gpt(device: &mut dyn BlockDevice) -> Result<Gpt> {
+    let info = device.info();
+    if info.block_count < 3 {
+        return Err(Error::new(Status::NotFound));
+    }
+
+    match read_table(device, 1) {
+        Ok(gpt) => Ok(gpt),
+        // Fall back to the backup, reporting the primary's error if it
+        // is unusable too
+        Err(err) if err.status() == Status::IoError => {
+            read_table(device, info.block_count - 1).map_err(|_| err)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Read the header at `lba` and the entry array it describes
+fn read_table(device: &mut dyn BlockDevice, lba: u64) -> Result<Gpt> {
+    let info = device.info();
+    let block_size = info.block_size as usize;
+
+    let mut block = vec![0u8; block_size];
+    device.read_blocks(lba, &mut block)?;
+    let header = GptHeader::parse(&block)?;
+    if header.my_lba != lba || header.last_usable_lba >= info.block_count {
+        return Err(Error::new(Status::IoError));
+    }
+
+    let array_size = header.entry_array_size();
+    let array_blocks = array_size.div_ceil(block_size) as u64;
+    if header.entry_lba.checked_add(array_blocks).map_or(true, |end| end > info.block_count) {
+        return Err(Error::new(Status::IoError));
+    }
+    let mut entries = vec![0u8; array_blocks as usize * block_size];
+    device.read_blocks(header.entry_lba, &mut entries)?;
+    if crc32(&entries[..array_size]) != header.entry_array_crc32 {
+        return Err(Error::new(Status::IoError));
+    }
+
+    let partitions = entries[..array_size]
+        .chunks_exact(header.entry_size as usize)
+        .enumerate()
+        .filter_map(|(i, entry)| Partition::parse(entry, i as u32 + 1))
+        .filter(|p| {
+            p.first_lba <= p.last_lba
+                && p.first_lba >= header.first_usable_lba
+                && p.last_lba <= header.last_usable_lba
+        })
+        .collect();
+
+    Ok(Gpt { header, partitions })
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+fn get_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockInfo;
+
+    const BLOCK: usize = 512;
+
+    struct RamDisk(Vec<u8>);
+
+    impl BlockDevice for RamDisk {
+        fn info(&self) -> BlockInfo {
+            BlockInfo {
+                block_size: BLOCK as u32,
+                max_transfer: 0,
+                block_count: (self.0.len() / BLOCK) as u64,
+            }
+        }
+
+        fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+            let start = lba as usize * BLOCK;
+            buf.copy_from_slice(&self.0[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn write_blocks(&mut self, _lba: u64, _buf: &[u8]) -> Result<()> {
+            Err(Error::new(Status::NotSupported))
+        }
+    }
+
+    fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+        buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(buf: &mut [u8], offset: usize, value: u64) {
+        buf[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// 128-block disk with one partition at blocks 34..=40 named "root"
+    fn make_disk() -> RamDisk {
+        let blocks = 128;
+        let mut disk = vec![0u8; blocks * BLOCK];
+
+        // Entry array: 4 entries of 128 bytes at LBA 2
+        let entries = 2 * BLOCK;
+        disk[entries..entries + 16].copy_from_slice(&Guid::LINUX_FS.0);
+        disk[entries + 16] = 0x42;
+        put_u64(&mut disk, entries + 32, 34);
+        put_u64(&mut disk, entries + 40, 40);
+        for (i, c) in "root".encode_utf16().enumerate() {
+            disk[entries + 56 + 2 * i..entries + 58 + 2 * i].copy_from_slice(&c.to_le_bytes());
+        }
+        let array_crc = crc32(&disk[entries..entries + 4 * 128]);
+
+        let hdr = BLOCK;
+        disk[hdr..hdr + 8].copy_from_slice(&GPT_SIGNATURE);
+        put_u32(&mut disk, hdr + 8, 0x0001_0000);
+        put_u32(&mut disk, hdr + 12, 92);
+        put_u64(&mut disk, hdr + 24, 1);
+        put_u64(&mut disk, hdr + 32, blocks as u64 - 1);
+        put_u64(&mut disk, hdr + 40, 34);
+        put_u64(&mut disk, hdr + 48, blocks as u64 - 34);
+        put_u64(&mut disk, hdr + 72, 2);
+        put_u32(&mut disk, hdr + 80, 4);
+        put_u32(&mut disk, hdr + 84, 128);
+        put_u32(&mut disk, hdr + 88, array_crc);
+        let crc = crc32(&disk[hdr..hdr + 92]);
+        put_u32(&mut disk, hdr + 16, crc);
+
+        RamDisk(disk)
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_guid_display() {
+        assert_eq!(
+            alloc::format!("{}", Guid::EFI_SYSTEM),
+            "C12A7328-F81F-11D2-BA4B-00A0C93EC93B"
+        );
+    }
+
+    #[test]
+    fn test_read_gpt() {
+        let mut disk = make_disk();
+        let gpt = read_gpt(&mut disk).unwrap();
+        assert_eq!(gpt.header.entry_count, 4);
+        assert_eq!(gpt.partitions.len(), 1);
+
+        let part = &gpt.partitions[0];
+        assert_eq!(part.index, 1);
+        assert_eq!(part.type_guid.type_name(), Some("linux-fs"));
+        assert_eq!(part.block_count(), 7);
+        assert_eq!(part.name, "root");
+    }
+
+    #[test]
+    fn test_rejects_corrupt_header() {
+        let mut disk = make_disk();
+        disk.0[BLOCK + 40] ^= 1;
+        // No valid backup either
+        assert_eq!(read_gpt(&mut disk).unwrap_err().status(), Status::IoError);
+
+        let mut blank = RamDisk(vec![0u8; 128 * BLOCK]);
+        assert_eq!(read_gpt(&mut blank).unwrap_err().status(), Status::NotFound);
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Driver Host
+//!
+//! A driver host owns one device channel. It waits for lifecycle messages
+//! from the device manager, forwards them to the bound driver and replies
+//! with the driver's status.
+//!
+//! Drivers are compiled into the host and listed in a static table of
+//! `DriverEntry` records; on `Bind` the host instantiates the first entry
+//! whose `matches` accepts the device.
+//!
+//! `LocalDevice` is the manager half for hosts living in the caller's
+//! process, as in `devhost` and the test tools.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use libipc::Channel;
+use libsys::{Error, Handle, Result, Status};
+
+use crate::protocol::{DeviceDescriptor, Message, HEADER_SIZE, MAX_BODY_SIZE, MAX_MESSAGE_SIZE};
+
+/// Client connection handed out by `Driver::open`
+pub struct Connection {
+    /// Class-specific info record, sent as the reply body
+    pub info: Vec<u8>,
+
+    /// Client ends of the class protocol
+    pub handles: Vec<Handle>,
+}
+
+/// A device driver
+///
+/// One instance is created per bound device.
+pub trait Driver {
+    /// Start driving the device reached through `device`
+    fn bind(&mut self, descriptor: &DeviceDescriptor, device: Handle) -> Result<()>;
+
+    /// Stop the device and release its resources
+    fn unbind(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Enter power state `state`
+    fn suspend(&mut self, _state: u32) -> Result<()> {
+        Ok(())
+    }
+
+    /// Return to the running state
+    fn resume(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Connect a client to instance `instance` of the device's class
+    /// protocol
+    ///
+    /// Instance 0 is the device itself; drivers that publish child
+    /// devices number them from 1 and fail with `Status::NotFound` past
+    /// the last one.
+    fn open(&mut self, _instance: u32) -> Result<Connection> {
+        Err(Error::new(Status::NotSupported))
+    }
+
+    /// Service device queues and client connections
+    ///
+    /// Returns true if any work was done.
+    fn poll(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Driver table entry
+pub struct DriverEntry {
+    /// Driver name, for logs
+    pub name: &'static str,
+
+    /// Whether the driver supports a device
+    pub matches: fn(&DeviceDescriptor) -> bool,
+
+    /// Instantiate the driver
+    pub create: fn() -> Box<dyn Driver>,
+}
+
+/// Find the first driver in `table` that supports `descriptor`
+pub fn find_driver<'a>(table: &'a [DriverEntry], descriptor: &DeviceDescriptor) -> Option<&'a DriverEntry> {
+    table.iter().find(|entry| (entry.matches)(descriptor))
+}
+
+/// Raw status sent back when a request fails
+fn reply_status(err: Error) -> i32 {
+    -(err.status().into_raw())
+}
+
+/// Serves lifecycle messages for one device
+pub struct DriverHost {
+    /// Channel to the device manager
+    channel: Channel,
+
+    /// Drivers available in this host
+    table: &'static [DriverEntry],
+
+    /// Bound driver and its table entry
+    bound: Option<(&'static DriverEntry, Box<dyn Driver>)>,
+}
+
+impl DriverHost {
+    /// Create a host serving `channel` with drivers from `table`
+    pub fn new(channel: Channel, table: &'static [DriverEntry]) -> Self {
+        Self {
+            channel,
+            table,
+            bound: None,
+        }
+    }
+
+    /// Name of the bound driver, if any
+    pub fn bound_driver(&self) -> Option<&'static str> {
+        self.bound.as_ref().map(|(entry, _)| entry.name)
+    }
+
+    /// Dispatch one decoded message
+    ///
+    /// `Open` yields the connection to return to the caller.
+    pub fn dispatch(&mut self, message: Message, handles: &mut Vec<Handle>) -> Result<Option<Connection>> {
+        match message {
+            Message::Bind(descriptor) => {
+                if self.bound.is_some() {
+                    return Err(Error::new(Status::AlreadyExists));
+                }
+                if handles.len() != 1 {
+                    return Err(Error::new(Status::InvalidArgs));
+                }
+                let entry = find_driver(self.table, &descriptor).ok_or(Error::new(Status::NotSupported))?;
+                let mut driver = (entry.create)();
+                driver.bind(&descriptor, handles.remove(0))?;
+                self.bound = Some((entry, driver));
+                Ok(None)
+            }
+            Message::Unbind => {
+                let (_, mut driver) = self.bound.take().ok_or(Error::new(Status::BadState))?;
+                driver.unbind().map(|()| None)
+            }
+            Message::Suspend(state) => self.driver()?.suspend(state).map(|()| None),
+            Message::Resume => self.driver()?.resume().map(|()| None),
+            Message::Open(instance) => {
+                let connection = self.driver()?.open(instance)?;
+                if connection.info.len() > MAX_BODY_SIZE {
+                    return Err(Error::new(Status::BufferTooSmall));
+                }
+                Ok(Some(connection))
+            }
+            Message::Reply => Err(Error::new(Status::NotSupported)),
+        }
+    }
+
+    fn driver(&mut self) -> Result<&mut Box<dyn Driver>> {
+        self.bound
+            .as_mut()
+            .map(|(_, driver)| driver)
+            .ok_or(Error::new(Status::BadState))
+    }
+
+    /// Read, dispatch and answer one message
+    ///
+    /// Returns false once the driver has been unbound and the host should
+    /// exit.
+    pub fn serve_one(&mut self) -> Result<bool> {
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+        let len = self.channel.read(&mut buf, &mut handles)?;
+        let (header, message) = Message::decode(&buf[..len])?;
+
+        let (status, connection) = match self.dispatch(message, &mut handles) {
+            Ok(connection) => (0, connection),
+            Err(err) => (reply_status(err), None),
+        };
+
+        let mut len = Message::Reply.encode(header.txid, status, &mut buf)?;
+        let mut reply_handles: &[Handle] = &[];
+        if let Some(connection) = &connection {
+            buf[HEADER_SIZE..HEADER_SIZE + connection.info.len()].copy_from_slice(&connection.info);
+            len += connection.info.len();
+            reply_handles = &connection.handles;
+        }
+        self.channel.write(&buf[..len], reply_handles)?;
+        if let Some(connection) = connection {
+            // The kernel moved the handles to the client
+            connection.handles.into_iter().for_each(core::mem::forget);
+        }
+
+        Ok(!(message == Message::Unbind && status == 0))
+    }
+
+    /// Let the bound driver service its queues
+    pub fn poll(&mut self) -> Result<bool> {
+        match self.bound.as_mut() {
+            Some((_, driver)) => driver.poll(),
+            None => Ok(false),
+        }
+    }
+
+    /// Serve messages until the driver is unbound
+    pub fn serve(&mut self) -> Result<()> {
+        while self.serve_one()? {}
+        Ok(())
+    }
+}
+
+/// A device bound to a driver host in the same process
+///
+/// Holds the manager end of the lifecycle channel. Manager and host share
+/// a thread, so each request is served inline before its reply is read.
+pub struct LocalDevice {
+    channel: Channel,
+    host: DriverHost,
+    next_txid: u32,
+}
+
+impl LocalDevice {
+    /// Bind `device` to the first driver in `table` that accepts it
+    pub fn bind(table: &'static [DriverEntry], descriptor: DeviceDescriptor, device: Handle) -> Result<Self> {
+        let (channel, host_end) = Channel::create()?;
+        let mut local = Self {
+            channel,
+            host: DriverHost::new(host_end, table),
+            next_txid: 1,
+        };
+        local.request(Message::Bind(descriptor), alloc::vec![device])?;
+        Ok(local)
+    }
+
+    /// The host serving the device
+    pub fn host(&self) -> &DriverHost {
+        &self.host
+    }
+
+    /// Mutable access to the host, for polling
+    pub fn host_mut(&mut self) -> &mut DriverHost {
+        &mut self.host
+    }
+
+    /// Send `message` and wait for its reply
+    ///
+    /// `handles` are moved to the host. Returns the reply body and any
+    /// handles it carried.
+    pub fn request(&mut self, message: Message, handles: Vec<Handle>) -> Result<(Vec<u8>, Vec<Handle>)> {
+        let txid = self.next_txid;
+        self.next_txid = self.next_txid.wrapping_add(1);
+
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        let len = message.encode(txid, 0, &mut buf)?;
+        self.channel.write(&buf[..len], &handles)?;
+        handles.into_iter().for_each(core::mem::forget);
+        self.host.serve_one()?;
+
+        let mut reply_handles = Vec::new();
+        let len = self.channel.read(&mut buf, &mut reply_handles)?;
+        let (header, reply) = Message::decode(&buf[..len])?;
+        if reply != Message::Reply || header.txid != txid {
+            return Err(Error::new(Status::BadState));
+        }
+        if header.status != 0 {
+            return Err(Error::from_raw(-header.status));
+        }
+        Ok((buf[HEADER_SIZE..len].to_vec(), reply_handles))
+    }
+
+    /// Open instance `instance` of the device's class protocol
+    pub fn open(&mut self, instance: u32) -> Result<(Vec<u8>, Vec<Handle>)> {
+        self.request(Message::Open(instance), Vec::new())
+    }
+}
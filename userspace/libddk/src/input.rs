@@ -0,0 +1,903 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Input Device Class
+//!
+//! Input drivers implement `InputDevice`. Opening the device returns an
+//! `InputInfo` record and a FIFO the server fills with `KeyEvent`
+//! records, one per key press or release.
+//!
+//! Key codes are HID keyboard usages whatever the hardware reports, so a
+//! consumer handles USB, PS/2 and other keyboards alike. Each event also
+//! carries the modifier and lock state in effect after it; `KeyState`
+//! keeps that state for keyboards that only report single keys.
+//! `terminal_bytes` turns presses into the bytes a VT100-style terminal
+//! would send, for consumers expecting character input, and
+//! `TerminalDecoder` goes the other way, for input from a serial
+//! terminal.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use libipc::Fifo;
+use libsys::{Error, Handle, Result, Status};
+
+use crate::host::Connection;
+
+/// Input device kinds
+pub mod input_kind {
+    pub const KEYBOARD: u16 = 1;
+}
+
+/// Input device parameters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputInfo {
+    /// `input_kind` value
+    pub kind: u16,
+
+    /// Vendor and product of the device, where the bus has them
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+impl InputInfo {
+    /// Size of the encoded record
+    pub const SIZE: usize = 8;
+
+    /// Encode as the `Open` reply body
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..2].copy_from_slice(&self.kind.to_le_bytes());
+        buf[2..4].copy_from_slice(&self.vendor_id.to_le_bytes());
+        buf[4..6].copy_from_slice(&self.product_id.to_le_bytes());
+        buf
+    }
+
+    /// Decode an `Open` reply body
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE {
+            return None;
+        }
+        Some(Self {
+            kind: u16::from_le_bytes([buf[0], buf[1]]),
+            vendor_id: u16::from_le_bytes([buf[2], buf[3]]),
+            product_id: u16::from_le_bytes([buf[4], buf[5]]),
+        })
+    }
+}
+
+/// Key codes (HID keyboard usages)
+pub mod key {
+    pub const A: u16 = 0x04;
+    pub const Z: u16 = 0x1D;
+    pub const DIGIT_1: u16 = 0x1E;
+    pub const DIGIT_0: u16 = 0x27;
+    pub const ENTER: u16 = 0x28;
+    pub const ESCAPE: u16 = 0x29;
+    pub const BACKSPACE: u16 = 0x2A;
+    pub const TAB: u16 = 0x2B;
+    pub const SPACE: u16 = 0x2C;
+    pub const MINUS: u16 = 0x2D;
+    pub const EQUAL: u16 = 0x2E;
+    pub const LEFT_BRACE: u16 = 0x2F;
+    pub const RIGHT_BRACE: u16 = 0x30;
+    pub const BACKSLASH: u16 = 0x31;
+    pub const NON_US_HASH: u16 = 0x32;
+    pub const SEMICOLON: u16 = 0x33;
+    pub const APOSTROPHE: u16 = 0x34;
+    pub const GRAVE: u16 = 0x35;
+    pub const COMMA: u16 = 0x36;
+    pub const DOT: u16 = 0x37;
+    pub const SLASH: u16 = 0x38;
+    pub const CAPS_LOCK: u16 = 0x39;
+    pub const F1: u16 = 0x3A;
+    pub const F2: u16 = 0x3B;
+    pub const F3: u16 = 0x3C;
+    pub const F4: u16 = 0x3D;
+    pub const F5: u16 = 0x3E;
+    pub const F6: u16 = 0x3F;
+    pub const F7: u16 = 0x40;
+    pub const F8: u16 = 0x41;
+    pub const F9: u16 = 0x42;
+    pub const F10: u16 = 0x43;
+    pub const F11: u16 = 0x44;
+    pub const F12: u16 = 0x45;
+    pub const PRINT_SCREEN: u16 = 0x46;
+    pub const SCROLL_LOCK: u16 = 0x47;
+    pub const PAUSE: u16 = 0x48;
+    pub const INSERT: u16 = 0x49;
+    pub const HOME: u16 = 0x4A;
+    pub const PAGE_UP: u16 = 0x4B;
+    pub const DELETE: u16 = 0x4C;
+    pub const END: u16 = 0x4D;
+    pub const PAGE_DOWN: u16 = 0x4E;
+    pub const RIGHT: u16 = 0x4F;
+    pub const LEFT: u16 = 0x50;
+    pub const DOWN: u16 = 0x51;
+    pub const UP: u16 = 0x52;
+    pub const NUM_LOCK: u16 = 0x53;
+    pub const KP_SLASH: u16 = 0x54;
+    pub const KP_ASTERISK: u16 = 0x55;
+    pub const KP_MINUS: u16 = 0x56;
+    pub const KP_PLUS: u16 = 0x57;
+    pub const KP_ENTER: u16 = 0x58;
+    pub const KP_1: u16 = 0x59;
+    pub const KP_2: u16 = 0x5A;
+    pub const KP_3: u16 = 0x5B;
+    pub const KP_4: u16 = 0x5C;
+    pub const KP_5: u16 = 0x5D;
+    pub const KP_6: u16 = 0x5E;
+    pub const KP_7: u16 = 0x5F;
+    pub const KP_8: u16 = 0x60;
+    pub const KP_9: u16 = 0x61;
+    pub const KP_0: u16 = 0x62;
+    pub const KP_DOT: u16 = 0x63;
+    pub const NON_US_BACKSLASH: u16 = 0x64;
+    pub const MENU: u16 = 0x65;
+    pub const LEFT_CTRL: u16 = 0xE0;
+    pub const LEFT_SHIFT: u16 = 0xE1;
+    pub const LEFT_ALT: u16 = 0xE2;
+    pub const LEFT_META: u16 = 0xE3;
+    pub const RIGHT_CTRL: u16 = 0xE4;
+    pub const RIGHT_SHIFT: u16 = 0xE5;
+    pub const RIGHT_ALT: u16 = 0xE6;
+    pub const RIGHT_META: u16 = 0xE7;
+}
+
+/// Modifier and lock bits
+///
+/// The low byte matches the modifier byte of a HID keyboard report.
+pub mod modifiers {
+    pub const LEFT_CTRL: u16 = 1 << 0;
+    pub const LEFT_SHIFT: u16 = 1 << 1;
+    pub const LEFT_ALT: u16 = 1 << 2;
+    pub const LEFT_META: u16 = 1 << 3;
+    pub const RIGHT_CTRL: u16 = 1 << 4;
+    pub const RIGHT_SHIFT: u16 = 1 << 5;
+    pub const RIGHT_ALT: u16 = 1 << 6;
+    pub const RIGHT_META: u16 = 1 << 7;
+    pub const CAPS_LOCK: u16 = 1 << 8;
+    pub const NUM_LOCK: u16 = 1 << 9;
+    pub const SCROLL_LOCK: u16 = 1 << 10;
+
+    /// Either key of a pair
+    pub const CTRL: u16 = LEFT_CTRL | RIGHT_CTRL;
+    pub const SHIFT: u16 = LEFT_SHIFT | RIGHT_SHIFT;
+    pub const ALT: u16 = LEFT_ALT | RIGHT_ALT;
+    pub const META: u16 = LEFT_META | RIGHT_META;
+}
+
+/// Event flags
+pub mod key_flags {
+    /// The key went down (clear: it went up)
+    pub const PRESSED: u16 = 0x0001;
+}
+
+/// One key press or release
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// `key` code
+    pub code: u16,
+
+    /// `modifiers` bits after the event
+    pub modifiers: u16,
+
+    /// `key_flags` bits
+    pub flags: u16,
+
+    /// Reserved, must be 0
+    pub reserved: u16,
+}
+
+impl KeyEvent {
+    /// A press (`pressed`) or release of `code`
+    pub fn new(code: u16, pressed: bool, modifiers: u16) -> Self {
+        Self {
+            code,
+            modifiers,
+            flags: if pressed { key_flags::PRESSED } else { 0 },
+            reserved: 0,
+        }
+    }
+
+    /// Whether the key went down
+    pub fn pressed(&self) -> bool {
+        self.flags & key_flags::PRESSED != 0
+    }
+
+    /// Size of the encoded record
+    pub const SIZE: usize = 8;
+
+    /// Encode for a byte stream
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..2].copy_from_slice(&self.code.to_le_bytes());
+        buf[2..4].copy_from_slice(&self.modifiers.to_le_bytes());
+        buf[4..6].copy_from_slice(&self.flags.to_le_bytes());
+        buf
+    }
+
+    /// Decode a record written by `to_bytes`
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE {
+            return None;
+        }
+        Some(Self {
+            code: u16::from_le_bytes([buf[0], buf[1]]),
+            modifiers: u16::from_le_bytes([buf[2], buf[3]]),
+            flags: u16::from_le_bytes([buf[4], buf[5]]),
+            reserved: 0,
+        })
+    }
+}
+
+/// Modifier and lock state of a keyboard that reports one key at a time
+///
+/// A key the keyboard repeats while it is held toggles its lock only
+/// once.
+pub struct KeyState {
+    /// Keys down, one bit per code below 256
+    down: [u64; 4],
+
+    /// `modifiers` bits
+    modifiers: u16,
+}
+
+impl KeyState {
+    pub const fn new() -> Self {
+        Self {
+            down: [0; 4],
+            modifiers: 0,
+        }
+    }
+
+    /// Record a press or release and return its event
+    pub fn update(&mut self, code: u16, pressed: bool) -> KeyEvent {
+        let (word, bit) = ((code as usize / 64) & 3, 1u64 << (code % 64));
+        let repeat = code < 256 && pressed && self.down[word] & bit != 0;
+        if code < 256 {
+            if pressed {
+                self.down[word] |= bit;
+            } else {
+                self.down[word] &= !bit;
+            }
+        }
+
+        match code {
+            key::LEFT_CTRL..=key::RIGHT_META => {
+                let mask = 1 << (code - key::LEFT_CTRL);
+                if pressed {
+                    self.modifiers |= mask;
+                } else {
+                    self.modifiers &= !mask;
+                }
+            }
+            key::CAPS_LOCK if pressed && !repeat => self.modifiers ^= modifiers::CAPS_LOCK,
+            key::NUM_LOCK if pressed && !repeat => self.modifiers ^= modifiers::NUM_LOCK,
+            key::SCROLL_LOCK if pressed && !repeat => self.modifiers ^= modifiers::SCROLL_LOCK,
+            _ => {}
+        }
+        KeyEvent::new(code, pressed, self.modifiers)
+    }
+
+    /// Current `modifiers` bits
+    pub fn modifiers(&self) -> u16 {
+        self.modifiers
+    }
+}
+
+impl Default for KeyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An input device
+pub trait InputDevice {
+    /// Device parameters
+    fn info(&self) -> InputInfo;
+
+    /// The next event, if any
+    fn read_event(&mut self) -> Result<Option<KeyEvent>>;
+}
+
+/// ============================================================================
+/// FIFO Protocol
+/// ============================================================================
+
+/// Number of events the FIFO holds
+pub const INPUT_FIFO_DEPTH: usize = 64;
+
+/// Events held back while the client isn't reading; older ones are
+/// dropped past this
+const MAX_PENDING: usize = 256;
+
+/// Serves the input protocol for one client
+pub struct InputServer {
+    events: Fifo,
+
+    /// Events not yet written to the FIFO
+    pending: VecDeque<KeyEvent>,
+}
+
+impl InputServer {
+    /// Create a server
+    ///
+    /// The only handle in the returned connection is the event FIFO.
+    pub fn create(info: &InputInfo) -> Result<(Self, Connection)> {
+        let (events, client) = Fifo::create(INPUT_FIFO_DEPTH, core::mem::size_of::<KeyEvent>())?;
+        let mut handles: Vec<Handle> = Vec::new();
+        handles.push(client.into_handle());
+
+        let connection = Connection {
+            info: info.to_bytes().to_vec(),
+            handles,
+        };
+        let server = Self {
+            events,
+            pending: VecDeque::new(),
+        };
+        Ok((server, connection))
+    }
+
+    /// Move events from `device` to the client
+    ///
+    /// Returns true if any event moved.
+    pub fn poll(&mut self, device: &mut dyn InputDevice) -> Result<bool> {
+        while let Some(event) = device.read_event()? {
+            if self.pending.len() == MAX_PENDING {
+                self.pending.pop_front();
+            }
+            self.pending.push_back(event);
+        }
+        if self.pending.is_empty() {
+            return Ok(false);
+        }
+
+        let count = self.events.write(self.pending.make_contiguous())?;
+        self.pending.drain(..count);
+        Ok(count > 0)
+    }
+}
+
+/// Client side of the input protocol
+pub struct InputClient {
+    info: InputInfo,
+    events: Fifo,
+}
+
+impl InputClient {
+    /// Connect using the body and handles of an `Open` reply
+    pub fn new(body: &[u8], handles: Vec<Handle>) -> Result<Self> {
+        let info = InputInfo::from_bytes(body).ok_or(Error::new(Status::InvalidArgs))?;
+        let [events] = <[Handle; 1]>::try_from(handles).map_err(|_| Error::new(Status::InvalidArgs))?;
+        Ok(Self {
+            info,
+            events: unsafe { Fifo::from_handle(events) },
+        })
+    }
+}
+
+impl InputDevice for InputClient {
+    fn info(&self) -> InputInfo {
+        self.info
+    }
+
+    fn read_event(&mut self) -> Result<Option<KeyEvent>> {
+        let mut event = KeyEvent::default();
+        if self.events.read(core::slice::from_mut(&mut event))? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(event))
+    }
+}
+
+/// ============================================================================
+/// Terminal Translation
+/// ============================================================================
+
+/// Characters for `key::DIGIT_1` through `key::SLASH`, unshifted and
+/// shifted, on a US layout
+const US_KEYS: [(u8, u8); 27] = [
+    (b'1', b'!'),
+    (b'2', b'@'),
+    (b'3', b'#'),
+    (b'4', b'$'),
+    (b'5', b'%'),
+    (b'6', b'^'),
+    (b'7', b'&'),
+    (b'8', b'*'),
+    (b'9', b'('),
+    (b'0', b')'),
+    (b'\r', b'\r'),
+    (0x1B, 0x1B),
+    (0x7F, 0x7F),
+    (b'\t', b'\t'),
+    (b' ', b' '),
+    (b'-', b'_'),
+    (b'=', b'+'),
+    (b'[', b'{'),
+    (b']', b'}'),
+    (b'\\', b'|'),
+    (b'#', b'~'),
+    (b';', b':'),
+    (b'\'', b'"'),
+    (b'`', b'~'),
+    (b',', b'<'),
+    (b'.', b'>'),
+    (b'/', b'?'),
+];
+
+/// Escape sequence of a cursor, editing or function key: the CSI number
+/// (0 for none) and final byte
+fn key_sequence(code: u16, modifiers: u16) -> Option<(u8, u8)> {
+    // Without Num Lock the keypad is a second cursor block
+    let code = if modifiers & self::modifiers::NUM_LOCK == 0 {
+        match code {
+            key::KP_1 => key::END,
+            key::KP_2 => key::DOWN,
+            key::KP_3 => key::PAGE_DOWN,
+            key::KP_4 => key::LEFT,
+            key::KP_6 => key::RIGHT,
+            key::KP_7 => key::HOME,
+            key::KP_8 => key::UP,
+            key::KP_9 => key::PAGE_UP,
+            key::KP_0 => key::INSERT,
+            key::KP_DOT => key::DELETE,
+            _ => code,
+        }
+    } else {
+        code
+    };
+
+    Some(match code {
+        key::UP => (0, b'A'),
+        key::DOWN => (0, b'B'),
+        key::RIGHT => (0, b'C'),
+        key::LEFT => (0, b'D'),
+        key::HOME => (0, b'H'),
+        key::END => (0, b'F'),
+        key::INSERT => (2, b'~'),
+        key::DELETE => (3, b'~'),
+        key::PAGE_UP => (5, b'~'),
+        key::PAGE_DOWN => (6, b'~'),
+        // F1-F4 are SS3 P-S; `terminal_bytes` handles the unmodified form
+        key::F1..=key::F4 => (0, b'P' + (code - key::F1) as u8),
+        key::F5..=key::F12 => {
+            const NUMBERS: [u8; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+            (NUMBERS[(code - key::F5) as usize], b'~')
+        }
+        _ => return None,
+    })
+}
+
+/// Write `value` in decimal
+fn write_decimal(buf: &mut [u8], len: &mut usize, value: u8) {
+    if value >= 10 {
+        buf[*len] = b'0' + value / 10;
+        *len += 1;
+    }
+    buf[*len] = b'0' + value % 10;
+    *len += 1;
+}
+
+/// Bytes a VT100-style terminal sends for a key press, on a US layout
+///
+/// Returns how many bytes of `buf` were written; releases, modifier keys
+/// and keys without a sequence give none. As in xterm, Ctrl turns
+/// characters into control codes, Alt prefixes them with Esc, and
+/// modified cursor and function keys carry an `;m` parameter.
+pub fn terminal_bytes(event: &KeyEvent, buf: &mut [u8; 8]) -> usize {
+    if !event.pressed() {
+        return 0;
+    }
+    let mods = event.modifiers;
+    let shift = mods & modifiers::SHIFT != 0;
+    let alt = mods & modifiers::ALT != 0;
+    let ctrl = mods & modifiers::CTRL != 0;
+
+    if let Some((number, final_byte)) = key_sequence(event.code, mods) {
+        let param = 1 + shift as u8 + 2 * alt as u8 + 4 * ctrl as u8;
+        if number == 0 && (b'P'..=b'S').contains(&final_byte) && param == 1 {
+            buf[..3].copy_from_slice(&[0x1B, b'O', final_byte]);
+            return 3;
+        }
+
+        buf[0] = 0x1B;
+        buf[1] = b'[';
+        let mut len = 2;
+        if number > 0 {
+            write_decimal(buf, &mut len, number);
+        }
+        if param > 1 {
+            if number == 0 {
+                buf[len] = b'1';
+                len += 1;
+            }
+            buf[len] = b';';
+            len += 1;
+            write_decimal(buf, &mut len, param);
+        }
+        buf[len] = final_byte;
+        return len + 1;
+    }
+
+    let mut c = match event.code {
+        key::A..=key::Z => {
+            let upper = shift != (mods & modifiers::CAPS_LOCK != 0);
+            let base = if upper { b'A' } else { b'a' };
+            base + (event.code - key::A) as u8
+        }
+        key::DIGIT_1..=key::SLASH => {
+            let (plain, shifted) = US_KEYS[(event.code - key::DIGIT_1) as usize];
+            if shift {
+                shifted
+            } else {
+                plain
+            }
+        }
+        key::NON_US_BACKSLASH => {
+            if shift {
+                b'|'
+            } else {
+                b'\\'
+            }
+        }
+        key::KP_SLASH => b'/',
+        key::KP_ASTERISK => b'*',
+        key::KP_MINUS => b'-',
+        key::KP_PLUS => b'+',
+        key::KP_ENTER => b'\r',
+        // Only reached with Num Lock on
+        key::KP_1..=key::KP_9 => b'1' + (event.code - key::KP_1) as u8,
+        key::KP_0 => b'0',
+        key::KP_DOT => b'.',
+        _ => return 0,
+    };
+
+    if ctrl {
+        c = match c {
+            b' ' => 0,
+            0x40..=0x7E => c & 0x1F,
+            _ => c,
+        };
+    }
+    if alt {
+        buf[0] = 0x1B;
+        buf[1] = c;
+        return 2;
+    }
+    buf[0] = c;
+    1
+}
+
+/// Key and modifiers typing character `c`, on a US layout
+fn char_key(c: u8) -> Option<(u16, u16)> {
+    Some(match c {
+        b'a'..=b'z' => (key::A + (c - b'a') as u16, 0),
+        b'A'..=b'Z' => (key::A + (c - b'A') as u16, modifiers::LEFT_SHIFT),
+        b'\r' => (key::ENTER, 0),
+        b'\t' => (key::TAB, 0),
+        0x1B => (key::ESCAPE, 0),
+        0x7F => (key::BACKSPACE, 0),
+        0x00 => (key::SPACE, modifiers::LEFT_CTRL),
+        // Ctrl clears bits 6 and 5 of the character typed
+        0x01..=0x1A => (key::A + (c - 1) as u16, modifiers::LEFT_CTRL),
+        0x1C..=0x1F => {
+            let (code, mods) = char_key(c | 0x40)?;
+            (code, mods | modifiers::LEFT_CTRL)
+        }
+        0x20..=0x7E => {
+            // Skip the non-US key, whose characters the US keys also type
+            let position = |pick: fn(&(u8, u8)) -> u8| {
+                US_KEYS
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i as u16 != key::NON_US_HASH - key::DIGIT_1)
+                    .find(|&(_, keys)| pick(keys) == c)
+                    .map(|(i, _)| key::DIGIT_1 + i as u16)
+            };
+            if let Some(code) = position(|keys| keys.0) {
+                (code, 0)
+            } else {
+                (position(|keys| keys.1)?, modifiers::LEFT_SHIFT)
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Key of a CSI sequence ending in `final_byte` with first parameter
+/// `number`
+fn csi_key(number: u16, final_byte: u8) -> Option<u16> {
+    Some(match (number, final_byte) {
+        (_, b'A') => key::UP,
+        (_, b'B') => key::DOWN,
+        (_, b'C') => key::RIGHT,
+        (_, b'D') => key::LEFT,
+        (_, b'H') | (1 | 7, b'~') => key::HOME,
+        (_, b'F') | (4 | 8, b'~') => key::END,
+        (_, b'P'..=b'S') => key::F1 + (final_byte - b'P') as u16,
+        (2, b'~') => key::INSERT,
+        (3, b'~') => key::DELETE,
+        (5, b'~') => key::PAGE_UP,
+        (6, b'~') => key::PAGE_DOWN,
+        (15, b'~') => key::F5,
+        (17..=21, b'~') => key::F6 + (number - 17),
+        (23 | 24, b'~') => key::F11 + (number - 23),
+        _ => return None,
+    })
+}
+
+/// Where `TerminalDecoder` is in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Ground,
+
+    /// After Esc
+    Escape,
+
+    /// In a CSI sequence
+    Csi,
+
+    /// After Esc O
+    Ss3,
+}
+
+/// Turns the bytes a VT100-style terminal sends back into key events
+///
+/// Each key typed becomes a press and a release carrying the modifiers
+/// the bytes imply; modifier keys themselves give no events. Input that
+/// `terminal_bytes` produces decodes to the keys it came from. Esc alone
+/// can't be told from the start of a sequence until input goes quiet, so
+/// it is only reported by `flush`.
+pub struct TerminalDecoder {
+    state: DecodeState,
+
+    /// CSI parameters seen so far
+    params: [u16; 2],
+    count: usize,
+
+    /// Whether any CSI parameter bytes arrived
+    has_params: bool,
+}
+
+impl TerminalDecoder {
+    pub const fn new() -> Self {
+        Self {
+            state: DecodeState::Ground,
+            params: [0; 2],
+            count: 0,
+            has_params: false,
+        }
+    }
+
+    /// Decode one byte, passing any events to `emit`
+    pub fn feed(&mut self, byte: u8, mut emit: impl FnMut(KeyEvent)) {
+        match self.state {
+            DecodeState::Ground => {
+                if byte == 0x1B {
+                    self.state = DecodeState::Escape;
+                } else if let Some((code, mods)) = char_key(byte) {
+                    Self::key(code, mods, &mut emit);
+                }
+            }
+            DecodeState::Escape => match byte {
+                b'[' => {
+                    self.state = DecodeState::Csi;
+                    self.params = [0; 2];
+                    self.count = 0;
+                    self.has_params = false;
+                }
+                b'O' => self.state = DecodeState::Ss3,
+                _ => {
+                    self.state = DecodeState::Ground;
+                    if let Some((code, mods)) = char_key(byte) {
+                        Self::key(code, mods | modifiers::LEFT_ALT, &mut emit);
+                    }
+                }
+            },
+            DecodeState::Csi => match byte {
+                b'0'..=b'9' => {
+                    let param = &mut self.params[self.count];
+                    *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    self.has_params = true;
+                }
+                b';' => {
+                    self.count = (self.count + 1).min(self.params.len() - 1);
+                    self.has_params = true;
+                }
+                0x40..=0x7E => {
+                    self.state = DecodeState::Ground;
+                    if let Some(code) = csi_key(self.params[0], byte) {
+                        // xterm's modifier parameter is 1 plus a bitmap
+                        let bits = self.params[1].saturating_sub(1);
+                        let mut mods = 0;
+                        if bits & 1 != 0 {
+                            mods |= modifiers::LEFT_SHIFT;
+                        }
+                        if bits & 2 != 0 {
+                            mods |= modifiers::LEFT_ALT;
+                        }
+                        if bits & 4 != 0 {
+                            mods |= modifiers::LEFT_CTRL;
+                        }
+                        Self::key(code, mods, &mut emit);
+                    }
+                }
+                // Intermediate and private bytes don't matter here
+                0x20..=0x3F => {}
+                _ => self.state = DecodeState::Ground,
+            },
+            DecodeState::Ss3 => {
+                self.state = DecodeState::Ground;
+                if let Some(code) = csi_key(0, byte) {
+                    Self::key(code, 0, &mut emit);
+                }
+            }
+        }
+    }
+
+    /// Finish a held back Esc, or Alt with `[` or `O`, once input has
+    /// gone quiet
+    pub fn flush(&mut self, mut emit: impl FnMut(KeyEvent)) {
+        match self.state {
+            DecodeState::Escape => Self::key(key::ESCAPE, 0, &mut emit),
+            DecodeState::Csi if !self.has_params => Self::key(key::LEFT_BRACE, modifiers::LEFT_ALT, &mut emit),
+            DecodeState::Ss3 => Self::key(key::A + 14, modifiers::LEFT_ALT | modifiers::LEFT_SHIFT, &mut emit),
+            _ => {}
+        }
+        self.state = DecodeState::Ground;
+    }
+
+    /// Whether bytes are held back waiting for the rest of a sequence
+    pub fn pending(&self) -> bool {
+        self.state != DecodeState::Ground
+    }
+
+    fn key(code: u16, mods: u16, emit: &mut impl FnMut(KeyEvent)) {
+        emit(KeyEvent::new(code, true, mods));
+        emit(KeyEvent::new(code, false, mods));
+    }
+}
+
+impl Default for TerminalDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: u16, modifiers: u16) -> alloc::vec::Vec<u8> {
+        let mut buf = [0u8; 8];
+        let len = terminal_bytes(&KeyEvent::new(code, true, modifiers), &mut buf);
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn test_input_info_roundtrip() {
+        let info = InputInfo {
+            kind: input_kind::KEYBOARD,
+            vendor_id: 0x0627,
+            product_id: 0x0001,
+        };
+        assert_eq!(InputInfo::from_bytes(&info.to_bytes()), Some(info));
+        assert_eq!(InputInfo::from_bytes(&[0u8; 4]), None);
+        assert_eq!(core::mem::size_of::<KeyEvent>(), 8);
+
+        let event = KeyEvent::new(key::F5, true, modifiers::LEFT_SHIFT | modifiers::NUM_LOCK);
+        assert_eq!(KeyEvent::from_bytes(&event.to_bytes()), Some(event));
+    }
+
+    #[test]
+    fn test_key_state() {
+        let mut state = KeyState::new();
+        assert_eq!(state.update(key::LEFT_SHIFT, true).modifiers, modifiers::LEFT_SHIFT);
+        assert_eq!(state.update(key::A, true), KeyEvent::new(key::A, true, modifiers::LEFT_SHIFT));
+        assert_eq!(state.update(key::LEFT_SHIFT, false).modifiers, 0);
+
+        // A held lock key repeats without toggling again
+        assert_eq!(state.update(key::CAPS_LOCK, true).modifiers, modifiers::CAPS_LOCK);
+        assert_eq!(state.update(key::CAPS_LOCK, true).modifiers, modifiers::CAPS_LOCK);
+        state.update(key::CAPS_LOCK, false);
+        assert_eq!(state.update(key::CAPS_LOCK, true).modifiers, 0);
+        assert_eq!(state.update(key::RIGHT_ALT, true).modifiers, modifiers::RIGHT_ALT);
+    }
+
+    fn decode(decoder: &mut TerminalDecoder, bytes: &[u8]) -> alloc::vec::Vec<KeyEvent> {
+        let mut events = alloc::vec::Vec::new();
+        for &byte in bytes {
+            decoder.feed(byte, |event| events.push(event));
+        }
+        events
+    }
+
+    #[test]
+    fn test_terminal_decoder_roundtrip() {
+        let mut decoder = TerminalDecoder::new();
+        let mut check = |bytes: &[u8]| {
+            let events = decode(&mut decoder, bytes);
+            assert_eq!(events.len(), 2, "{:x?}", bytes);
+            assert!(events[0].pressed() && !events[1].pressed());
+            let mut buf = [0u8; 8];
+            let len = terminal_bytes(&events[0], &mut buf);
+            assert_eq!(&buf[..len], bytes);
+        };
+
+        for byte in (0u8..0x7F).filter(|&b| b != 0x1B) {
+            check(&[byte]);
+        }
+        for seq in [
+            &b"\x1b[A"[..],
+            b"\x1b[1;5D",
+            b"\x1b[3~",
+            b"\x1b[5;2~",
+            b"\x1bOP",
+            b"\x1b[1;2P",
+            b"\x1b[15~",
+            b"\x1b[24;7~",
+            b"\x1bx",
+            b"\x1b\x03",
+            b"\x1b\x1b",
+        ] {
+            check(seq);
+        }
+    }
+
+    #[test]
+    fn test_terminal_decoder_escape() {
+        let mut decoder = TerminalDecoder::new();
+        assert!(decode(&mut decoder, b"\x1b").is_empty());
+        assert!(decoder.pending());
+        let mut events = alloc::vec::Vec::new();
+        decoder.flush(|event| events.push(event));
+        assert_eq!(events[0], KeyEvent::new(key::ESCAPE, true, 0));
+        assert!(!decoder.pending());
+
+        // Unknown sequences are dropped whole
+        assert!(decode(&mut decoder, b"\x1b[?25h").is_empty());
+        assert_eq!(decode(&mut decoder, b"q")[0], KeyEvent::new(key::A + 16, true, 0));
+    }
+
+    #[test]
+    fn test_terminal_characters() {
+        assert_eq!(press(key::A, 0), b"a");
+        assert_eq!(press(key::A, modifiers::LEFT_SHIFT), b"A");
+        assert_eq!(press(key::A, modifiers::CAPS_LOCK), b"A");
+        assert_eq!(press(key::A, modifiers::CAPS_LOCK | modifiers::RIGHT_SHIFT), b"a");
+        assert_eq!(press(key::DIGIT_1, modifiers::LEFT_SHIFT), b"!");
+        assert_eq!(press(key::DIGIT_0, 0), b"0");
+        assert_eq!(press(key::SLASH, modifiers::LEFT_SHIFT), b"?");
+        assert_eq!(press(key::ENTER, 0), b"\r");
+        assert_eq!(press(key::BACKSPACE, 0), b"\x7f");
+        assert_eq!(press(key::A + 2, modifiers::LEFT_CTRL), b"\x03");
+        assert_eq!(press(key::LEFT_BRACE, modifiers::RIGHT_CTRL), b"\x1b");
+        assert_eq!(press(key::A + 23, modifiers::LEFT_ALT), b"\x1bx");
+        assert_eq!(press(key::LEFT_SHIFT, modifiers::LEFT_SHIFT), b"");
+
+        let mut buf = [0u8; 8];
+        assert_eq!(terminal_bytes(&KeyEvent::new(key::A, false, 0), &mut buf), 0);
+    }
+
+    #[test]
+    fn test_terminal_sequences() {
+        assert_eq!(press(key::UP, 0), b"\x1b[A");
+        assert_eq!(press(key::LEFT, modifiers::LEFT_CTRL), b"\x1b[1;5D");
+        assert_eq!(press(key::PAGE_UP, 0), b"\x1b[5~");
+        assert_eq!(press(key::PAGE_UP, modifiers::LEFT_SHIFT), b"\x1b[5;2~");
+        assert_eq!(press(key::PAGE_DOWN, modifiers::RIGHT_SHIFT), b"\x1b[6;2~");
+        assert_eq!(press(key::F1, 0), b"\x1bOP");
+        assert_eq!(press(key::F1, modifiers::LEFT_SHIFT), b"\x1b[1;2P");
+        assert_eq!(press(key::F12, modifiers::LEFT_CTRL | modifiers::LEFT_ALT), b"\x1b[24;7~");
+        assert_eq!(press(key::KP_8, 0), b"\x1b[A");
+        assert_eq!(press(key::KP_8, modifiers::NUM_LOCK), b"8");
+        assert_eq!(press(key::KP_ENTER, 0), b"\r");
+    }
+}
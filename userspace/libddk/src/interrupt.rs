@@ -0,0 +1,132 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Interrupt Objects
+//!
+//! Wrapper for kernel interrupt objects. A driver obtains one from its PCI
+//! device (`PciDevice::map_interrupt`) or creates one directly from a
+//! resource, then alternates `wait` and `ack`.
+
+use libsys::syscall::{syscall1, syscall2, syscall3, syscall4, SyscallNumber};
+use libsys::{Handle, Result, Rights, Status};
+
+use crate::check;
+
+/// Interrupt creation options (mirror the kernel's `interrupt_flags`)
+pub mod interrupt_flags {
+    /// Software-triggered interrupt
+    pub const VIRTUAL: u32 = 0x01;
+}
+
+/// Interrupt object handle
+#[derive(Debug)]
+pub struct Interrupt {
+    handle: Handle,
+}
+
+impl Interrupt {
+    /// Create an interrupt object from a raw handle
+    pub unsafe fn from_handle(handle: Handle) -> Self {
+        Self { handle }
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Bind an interrupt object to platform vector `vector`
+    ///
+    /// `resource` must grant access to the vector (currently the root
+    /// resource).
+    pub fn create(resource: &Handle, vector: u32) -> Result<Self> {
+        Self::create_etc(resource.raw(), vector, 0)
+    }
+
+    /// Create a virtual interrupt, triggered with `trigger`
+    pub fn create_virtual() -> Result<Self> {
+        Self::create_etc(0, 0, interrupt_flags::VIRTUAL)
+    }
+
+    fn create_etc(resource: u32, vector: u32, options: u32) -> Result<Self> {
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall4(
+                SyscallNumber::InterruptCreate as u64,
+                resource as u64,
+                vector as u64,
+                options as u64,
+                &mut out as *mut u32 as u64,
+            ))?;
+            Ok(Self::from_handle(Handle::from_raw(out, Rights::all())))
+        }
+    }
+
+    /// Wait for the next delivery and return its timestamp
+    ///
+    /// The kernel has no blocking interrupt wait yet, so this polls until
+    /// a delivery is pending.
+    pub fn wait(&self) -> Result<u64> {
+        loop {
+            match self.try_wait() {
+                Err(err) if err.status() == Status::WouldBlock => core::hint::spin_loop(),
+                result => return result,
+            }
+        }
+    }
+
+    /// Consume a pending delivery without waiting
+    ///
+    /// Fails with `Status::WouldBlock` if nothing is pending.
+    pub fn try_wait(&self) -> Result<u64> {
+        let mut timestamp: u64 = 0;
+        unsafe {
+            check(syscall2(
+                SyscallNumber::InterruptWait as u64,
+                self.handle.raw() as u64,
+                &mut timestamp as *mut u64 as u64,
+            ))?;
+        }
+        Ok(timestamp)
+    }
+
+    /// Acknowledge the last delivery and re-arm the interrupt
+    pub fn ack(&self) -> Result<()> {
+        unsafe {
+            check(syscall1(
+                SyscallNumber::InterruptAck as u64,
+                self.handle.raw() as u64,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Trigger a virtual interrupt
+    pub fn trigger(&self, timestamp: u64) -> Result<()> {
+        unsafe {
+            check(syscall3(
+                SyscallNumber::InterruptTrigger as u64,
+                self.handle.raw() as u64,
+                0, // options
+                timestamp,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Destroy the object, releasing its vector
+    ///
+    /// Pending and future waits fail.
+    pub fn destroy(self) -> Result<()> {
+        unsafe {
+            check(syscall1(
+                SyscallNumber::InterruptDestroy as u64,
+                self.handle.raw() as u64,
+            ))?;
+        }
+        Ok(())
+    }
+}
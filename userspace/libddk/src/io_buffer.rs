@@ -0,0 +1,75 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! I/O Buffers
+//!
+//! An `IoBuffer` is a VMO mapped into the caller's address space. Device
+//! protocols share one between client and server: FIFO records carry
+//! offsets into it instead of the data itself.
+
+use libsys::{vmar, Error, Result, Status, Vmo};
+
+use crate::mmio::map_flags;
+
+/// Mapped VMO shared between a client and a server
+pub struct IoBuffer {
+    vmo: Vmo,
+    vaddr: usize,
+    size: usize,
+}
+
+impl IoBuffer {
+    /// Create and map a VMO of `size` bytes
+    pub fn create(size: usize, name: &str) -> Result<Self> {
+        let vmo = Vmo::create(size as u64, Some(name))?;
+        Self::map(vmo, size)
+    }
+
+    /// Map the first `size` bytes of an existing VMO
+    pub fn map(vmo: Vmo, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let root = vmar::root_self()?;
+        let vaddr = vmar::map(&root, 0, &vmo, 0, size, map_flags::PERM_READ | map_flags::PERM_WRITE)?;
+        Ok(Self { vmo, vaddr, size })
+    }
+
+    /// Backing VMO
+    pub fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// Size in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Bytes `[offset, offset + len)`, if in range
+    pub fn slice(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        let end = offset.checked_add(len)?;
+        if end > self.size {
+            return None;
+        }
+        Some(unsafe { core::slice::from_raw_parts((self.vaddr + offset) as *const u8, len) })
+    }
+
+    /// Mutable bytes `[offset, offset + len)`, if in range
+    pub fn slice_mut(&mut self, offset: usize, len: usize) -> Option<&mut [u8]> {
+        let end = offset.checked_add(len)?;
+        if end > self.size {
+            return None;
+        }
+        Some(unsafe { core::slice::from_raw_parts_mut((self.vaddr + offset) as *mut u8, len) })
+    }
+
+    /// Unmap the buffer and close the VMO
+    pub fn close(self) -> Result<()> {
+        let root = vmar::root_self()?;
+        vmar::unmap(&root, self.vaddr, self.size)?;
+        self.vmo.into_handle().close()
+    }
+}
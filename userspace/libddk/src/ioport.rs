@@ -0,0 +1,85 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86 I/O Ports
+//!
+//! `IoPorts` asks the kernel to let the process use a range of I/O ports
+//! and then reads and writes them directly with `in` and `out`. Legacy
+//! devices such as the i8042 keyboard controller are only reachable this
+//! way.
+
+use libsys::syscall::{syscall3, SyscallNumber};
+use libsys::{Error, Handle, Result, Status};
+
+use crate::check;
+
+/// A range of I/O ports the process may use
+pub struct IoPorts {
+    base: u16,
+    len: u16,
+}
+
+impl IoPorts {
+    /// Request `len` ports from `base`
+    ///
+    /// `resource` must grant the ports (currently the root resource).
+    /// Fails with `NotSupported` except on x86_64.
+    pub fn request(resource: &Handle, base: u16, len: u16) -> Result<Self> {
+        if len == 0 || base as u32 + len as u32 > 0x10000 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        check(unsafe {
+            syscall3(
+                SyscallNumber::IoportsRequest as u64,
+                resource.raw() as u64,
+                base as u64,
+                len as u64,
+            )
+        })?;
+        Ok(Self { base, len })
+    }
+
+    /// First port of the range
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    /// Read the byte port at `offset` into the range
+    pub fn read8(&self, offset: u16) -> u8 {
+        assert!(offset < self.len);
+        unsafe { inb(self.base + offset) }
+    }
+
+    /// Write the byte port at `offset` into the range
+    pub fn write8(&self, offset: u16, value: u8) {
+        assert!(offset < self.len);
+        unsafe { outb(self.base + offset, value) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    core::arch::asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn outb(port: u16, value: u8) {
+    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+// `request` never succeeds elsewhere, so these are never reached
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn inb(_port: u16) -> u8 {
+    unreachable!()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn outb(_port: u16, _value: u8) {
+    unreachable!()
+}
@@ -0,0 +1,90 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Rustux Driver Development Kit (libddk)
+//!
+//! This library is the userspace side of the driver model:
+//! - Device lifecycle protocol spoken between the device manager and
+//!   driver hosts over channels
+//! - The `Driver` trait and a driver host loop
+//! - MMIO buffers backed by physical VMOs, and x86 I/O ports
+//! - Interrupt objects
+//! - DMA buffers (contiguous VMOs pinned through a BTI)
+//! - PCI device access
+//! - Block, ethernet and input device classes served over FIFOs
+//! - GPT parsing and a block stack publishing partitions as instances
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libddk::*;
+//!
+//! fn start(device: &PciDevice) -> libsys::Result<()> {
+//!     let regs = device.map_bar(0)?;
+//!     let irq = device.map_interrupt(0)?;
+//!     device.enable_bus_master(true)?;
+//!
+//!     let bti = Bti::create(0)?;
+//!     let ring = DmaBuffer::create(&bti, 4096)?;
+//!     regs.write64(0x10, ring.phys());
+//!
+//!     loop {
+//!         irq.wait()?;
+//!         let status = regs.read32(0x04);
+//!         regs.write32(0x04, status);
+//!         irq.ack()?;
+//!     }
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod block;
+pub mod dma;
+pub mod ethernet;
+pub mod gpt;
+pub mod host;
+pub mod input;
+pub mod interrupt;
+pub mod io_buffer;
+pub mod ioport;
+pub mod mmio;
+pub mod partition;
+pub mod pci;
+pub mod protocol;
+
+// Re-export commonly used types
+pub use block::{BlockClient, BlockDevice, BlockInfo, BlockServer, DeviceHealth};
+pub use dma::{Bti, DmaBuffer, Iommu, Pmt};
+pub use ethernet::{EthernetClient, EthernetDevice, EthernetInfo, EthernetServer};
+pub use host::{Connection, Driver, DriverEntry, DriverHost, LocalDevice};
+pub use input::{InputClient, InputDevice, InputInfo, InputServer, KeyEvent};
+pub use interrupt::Interrupt;
+pub use io_buffer::IoBuffer;
+pub use ioport::IoPorts;
+pub use mmio::MmioBuffer;
+pub use partition::{BlockStack, SubDevice};
+pub use pci::{PciBar, PciDevice, PciDeviceInfo};
+pub use protocol::{DeviceDescriptor, Message, MessageHeader, Ordinal};
+
+use libsys::{Error, Result, Status};
+
+/// Kernel status returned when an operation has nothing to report yet
+pub(crate) const RX_ERR_SHOULD_WAIT: i32 = -18;
+
+/// Convert a raw syscall return into a `Result`
+pub(crate) fn check(ret: u64) -> Result<u64> {
+    let status = ret as i32;
+    if status == RX_ERR_SHOULD_WAIT {
+        return Err(Error::new(Status::WouldBlock));
+    }
+    if status < 0 {
+        return Err(Error::from_raw(status));
+    }
+    Ok(ret)
+}
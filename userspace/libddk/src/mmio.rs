@@ -0,0 +1,170 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! MMIO Buffers
+//!
+//! An `MmioBuffer` maps a physical VMO covering device registers into the
+//! driver's address space and provides volatile register accessors.
+
+use libsys::syscall::{syscall4, SyscallNumber};
+use libsys::{vmar, Error, Handle, Result, Rights, Status, Vmo};
+
+use crate::check;
+
+/// VMAR mapping permissions (mirror the kernel's `vmar_options`)
+pub mod map_flags {
+    /// Readable mapping
+    pub const PERM_READ: u32 = 0x01;
+
+    /// Writable mapping
+    pub const PERM_WRITE: u32 = 0x02;
+}
+
+/// Page size used to round MMIO mappings
+const PAGE_SIZE: usize = 4096;
+
+/// Mapped device register window
+pub struct MmioBuffer {
+    /// Backing physical VMO
+    vmo: Vmo,
+
+    /// Base address of the mapping
+    vaddr: usize,
+
+    /// Offset of the window within the first mapped page
+    offset: usize,
+
+    /// Size of the window in bytes
+    size: usize,
+
+    /// Size of the mapping in bytes
+    mapped_size: usize,
+}
+
+impl MmioBuffer {
+    /// Map `size` bytes of `vmo` starting at `offset`
+    pub fn map(vmo: Vmo, offset: u64, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let page_offset = (offset as usize) & (PAGE_SIZE - 1);
+        let map_offset = offset - page_offset as u64;
+        let mapped_size = (page_offset + size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+        let root = vmar::root_self()?;
+        let vaddr = vmar::map(
+            &root,
+            0,
+            &vmo,
+            map_offset,
+            mapped_size,
+            map_flags::PERM_READ | map_flags::PERM_WRITE,
+        )?;
+
+        Ok(Self {
+            vmo,
+            vaddr,
+            offset: page_offset,
+            size,
+            mapped_size,
+        })
+    }
+
+    /// Create a physical VMO for `[paddr, paddr + size)` and map it
+    ///
+    /// `resource` must grant access to the range (currently the root
+    /// resource).
+    pub fn create_physical(resource: &Handle, paddr: u64, size: usize) -> Result<Self> {
+        let page_offset = paddr & (PAGE_SIZE as u64 - 1);
+        let vmo_size = (page_offset as usize + size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall4(
+                SyscallNumber::VmoCreatePhysical as u64,
+                resource.raw() as u64,
+                paddr - page_offset,
+                vmo_size as u64,
+                &mut out as *mut u32 as u64,
+            ))?;
+        }
+
+        let vmo = unsafe { Vmo::from_handle(Handle::from_raw(out, Rights::all())) };
+        Self::map(vmo, page_offset, size)
+    }
+
+    /// Backing VMO
+    pub fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// Virtual address of the first register
+    pub fn as_ptr(&self) -> *mut u8 {
+        (self.vaddr + self.offset) as *mut u8
+    }
+
+    /// Size of the register window in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn reg<T>(&self, offset: usize) -> *mut T {
+        assert!(
+            offset + core::mem::size_of::<T>() <= self.size,
+            "MMIO access out of range"
+        );
+        assert!(offset % core::mem::align_of::<T>() == 0, "unaligned MMIO access");
+        (self.vaddr + self.offset + offset) as *mut T
+    }
+
+    /// Read an 8-bit register
+    pub fn read8(&self, offset: usize) -> u8 {
+        unsafe { core::ptr::read_volatile(self.reg(offset)) }
+    }
+
+    /// Read a 16-bit register
+    pub fn read16(&self, offset: usize) -> u16 {
+        unsafe { core::ptr::read_volatile(self.reg(offset)) }
+    }
+
+    /// Read a 32-bit register
+    pub fn read32(&self, offset: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(self.reg(offset)) }
+    }
+
+    /// Read a 64-bit register
+    pub fn read64(&self, offset: usize) -> u64 {
+        unsafe { core::ptr::read_volatile(self.reg(offset)) }
+    }
+
+    /// Write an 8-bit register
+    pub fn write8(&self, offset: usize, value: u8) {
+        unsafe { core::ptr::write_volatile(self.reg(offset), value) }
+    }
+
+    /// Write a 16-bit register
+    pub fn write16(&self, offset: usize, value: u16) {
+        unsafe { core::ptr::write_volatile(self.reg(offset), value) }
+    }
+
+    /// Write a 32-bit register
+    pub fn write32(&self, offset: usize, value: u32) {
+        unsafe { core::ptr::write_volatile(self.reg(offset), value) }
+    }
+
+    /// Write a 64-bit register
+    pub fn write64(&self, offset: usize, value: u64) {
+        unsafe { core::ptr::write_volatile(self.reg(offset), value) }
+    }
+
+    /// Unmap the window and close the VMO
+    pub fn close(self) -> Result<()> {
+        let root = vmar::root_self()?;
+        vmar::unmap(&root, self.vaddr, self.mapped_size)?;
+        self.vmo.into_handle().close()
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Block Device Stack
+//!
+//! `BlockStack` sits between a block driver and its clients. It reads
+//! the device's partition table once and publishes one instance per
+//! partition alongside the whole device:
+//!
+//! - Instance 0: the whole device
+//! - Instance N: GPT partition entry N, in table order
+//!
+//! Every instance is served by its own `BlockServer`; partition servers
+//! see a `SubDevice` window of the parent, so requests cannot cross the
+//! partition's bounds.
+
+use alloc::vec::Vec;
+
+use libsys::{Error, Result, Status};
+
+use crate::block::{check_range, BlockDevice, BlockInfo, BlockServer, DeviceHealth};
+use crate::gpt::{self, Partition};
+use crate::host::Connection;
+
+/// A contiguous range of blocks of a parent device
+pub struct SubDevice<'a> {
+    parent: &'a mut dyn BlockDevice,
+    first_lba: u64,
+    block_count: u64,
+}
+
+impl<'a> SubDevice<'a> {
+    /// Window of `block_count` blocks starting at `first_lba`
+    pub fn new(parent: &'a mut dyn BlockDevice, first_lba: u64, block_count: u64) -> Result<Self> {
+        match first_lba.checked_add(block_count) {
+            Some(end) if end <= parent.info().block_count => Ok(Self {
+                parent,
+                first_lba,
+                block_count,
+            }),
+            _ => Err(Error::new(Status::InvalidArgs)),
+        }
+    }
+}
+
+impl BlockDevice for SubDevice<'_> {
+    fn info(&self) -> BlockInfo {
+        BlockInfo {
+            block_count: self.block_count,
+            ..self.parent.info()
+        }
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(&self.info(), lba, buf.len())?;
+        self.parent.read_blocks(self.first_lba + lba, buf)
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        check_range(&self.info(), lba, buf.len())?;
+        self.parent.write_blocks(self.first_lba + lba, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.parent.flush()
+    }
+
+    /// A partition reports the health of the whole drive
+    fn health(&mut self) -> Result<DeviceHealth> {
+        self.parent.health()
+    }
+}
+
+/// A block device and the partitions published on it
+pub struct BlockStack<D: BlockDevice> {
+    device: D,
+    partitions: Vec<Partition>,
+
+    /// Server per instance, once opened
+    servers: Vec<Option<BlockServer>>,
+}
+
+impl<D: BlockDevice> BlockStack<D> {
+    /// Scan `device` for partitions
+    ///
+    /// A device without a valid GPT publishes only instance 0.
+    pub fn new(mut device: D) -> Self {
+        let partitions = gpt::read_gpt(&mut device).map(|gpt| gpt.partitions).unwrap_or_default();
+        let mut servers = Vec::new();
+        servers.resize_with(partitions.len() + 1, || None);
+        Self {
+            device,
+            partitions,
+            servers,
+        }
+    }
+
+    /// The underlying device
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Partitions found on the device; entry `i` is instance `i + 1`
+    pub fn partitions(&self) -> &[Partition] {
+        &self.partitions
+    }
+
+    /// Number of published instances, including the whole device
+    pub fn instance_count(&self) -> u32 {
+        self.servers.len() as u32
+    }
+
+    /// Geometry of `instance`
+    pub fn instance_info(&self, instance: u32) -> Result<BlockInfo> {
+        let info = self.device.info();
+        match instance {
+            0 => Ok(info),
+            n => {
+                let part = self
+                    .partitions
+                    .get(n as usize - 1)
+                    .ok_or(Error::new(Status::NotFound))?;
+                Ok(BlockInfo {
+                    block_count: part.block_count(),
+                    ..info
+                })
+            }
+        }
+    }
+
+    /// Connect a client to `instance` with an I/O VMO of `io_size` bytes
+    ///
+    /// Each instance serves one client; opening it again replaces the
+    /// previous connection.
+    pub fn open(&mut self, instance: u32, io_size: usize) -> Result<Connection> {
+        let info = self.instance_info(instance)?;
+        let (server, connection) = BlockServer::create(&info, io_size)?;
+        self.servers[instance as usize] = Some(server);
+        Ok(connection)
+    }
+
+    /// Serve every open instance
+    ///
+    /// Returns true if any request was processed.
+    pub fn poll(&mut self) -> Result<bool> {
+        let mut progress = false;
+        for (instance, slot) in self.servers.iter_mut().enumerate() {
+            let server = match slot {
+                Some(server) => server,
+                None => continue,
+            };
+            progress |= match instance {
+                0 => server.poll(&mut self.device)?,
+                n => {
+                    let part = &self.partitions[n - 1];
+                    let mut sub = SubDevice::new(&mut self.device, part.first_lba, part.block_count())?;
+                    server.poll(&mut sub)?
+                }
+            };
+        }
+        Ok(progress)
+    }
+
+    /// Close all instances and return the device
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    struct RamDisk(Vec<u8>);
+
+    impl BlockDevice for RamDisk {
+        fn info(&self) -> BlockInfo {
+            BlockInfo {
+                block_size: 512,
+                max_transfer: 0,
+                block_count: (self.0.len() / 512) as u64,
+            }
+        }
+
+        fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+            let start = lba as usize * 512;
+            buf.copy_from_slice(&self.0[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+            let start = lba as usize * 512;
+            self.0[start..start + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sub_device_bounds() {
+        let mut disk = RamDisk(vec![0u8; 16 * 512]);
+        assert!(SubDevice::new(&mut disk, 10, 7).is_err());
+
+        let mut sub = SubDevice::new(&mut disk, 4, 4).unwrap();
+        assert_eq!(sub.info().block_count, 4);
+        sub.write_blocks(1, &[0xab; 512]).unwrap();
+        assert!(sub.write_blocks(4, &[0; 512]).is_err());
+        assert!(sub.read_blocks(3, &mut [0; 1024]).is_err());
+
+        assert_eq!(disk.0[5 * 512], 0xab);
+        assert_eq!(disk.0[4 * 512], 0);
+    }
+
+    #[test]
+    fn test_stack_without_gpt() {
+        let stack = BlockStack::new(RamDisk(vec![0u8; 16 * 512]));
+        assert_eq!(stack.instance_count(), 1);
+        assert_eq!(stack.instance_info(0).unwrap().block_count, 16);
+        assert_eq!(stack.instance_info(1).unwrap_err().status(), Status::NotFound);
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PCI Devices
+//!
+//! Wrapper for the kernel's PCI device handles: configuration space
+//! access, BAR mapping, bus mastering and interrupt setup.
+
+use libsys::syscall::{syscall2, syscall3, syscall4, SyscallNumber};
+use libsys::{Error, Handle, Result, Rights, Status, Vmo};
+
+use crate::check;
+use crate::interrupt::Interrupt;
+use crate::mmio::MmioBuffer;
+use crate::protocol::{bus_protocol, DeviceDescriptor};
+
+/// PCI IRQ modes (mirror the kernel's `pci_irq_mode`)
+pub mod irq_mode {
+    /// Legacy INTx
+    pub const LEGACY: u32 = 0;
+
+    /// MSI
+    pub const MSI: u32 = 1;
+
+    /// MSI-X
+    pub const MSI_X: u32 = 2;
+}
+
+/// PCI BAR types (mirror the kernel's `pci_bar_type`)
+pub mod bar_type {
+    /// Memory-mapped BAR
+    pub const MMIO: u32 = 0;
+
+    /// Port I/O BAR
+    pub const PIO: u32 = 1;
+}
+
+/// PCI device information (mirrors the kernel's `PciDeviceInfo`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PciDeviceInfo {
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub base_class: u8,
+    pub sub_class: u8,
+    pub prog_if: u8,
+    pub revision_id: u8,
+    pub bus_id: u8,
+    pub dev_id: u8,
+    pub func_id: u8,
+    pub irqs: [u32; 6],
+}
+
+impl PciDeviceInfo {
+    /// Descriptor offered to drivers when binding this device
+    pub fn descriptor(&self) -> DeviceDescriptor {
+        DeviceDescriptor {
+            protocol: bus_protocol::PCI,
+            vendor_id: self.vendor_id,
+            device_id: self.device_id,
+            base_class: self.base_class,
+            sub_class: self.sub_class,
+            prog_if: self.prog_if,
+            revision_id: self.revision_id,
+        }
+    }
+}
+
+/// PCI BAR information (mirrors the kernel's `PciBar`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PciBar {
+    /// Size of the BAR in bytes
+    pub size: u64,
+
+    /// `bar_type::MMIO` or `bar_type::PIO`
+    pub bar_type: u32,
+
+    /// Reserved
+    pub reserved: u32,
+
+    /// Port base for PIO BARs
+    pub addr: u32,
+}
+
+/// PCI device handle
+#[derive(Debug)]
+pub struct PciDevice {
+    handle: Handle,
+    info: PciDeviceInfo,
+}
+
+impl PciDevice {
+    /// Create a device from a raw handle and its information
+    pub unsafe fn from_handle(handle: Handle, info: PciDeviceInfo) -> Self {
+        Self { handle, info }
+    }
+
+    /// Get the `index`th device on the bus
+    ///
+    /// Fails with `Status::NotFound` past the last device.
+    pub fn get_nth(resource: &Handle, index: u32) -> Result<Self> {
+        let mut info = PciDeviceInfo::default();
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall4(
+                SyscallNumber::PciGetNthDevice as u64,
+                resource.raw() as u64,
+                index as u64,
+                &mut info as *mut PciDeviceInfo as u64,
+                &mut out as *mut u32 as u64,
+            ))?;
+            Ok(Self::from_handle(Handle::from_raw(out, Rights::all()), info))
+        }
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Take the underlying handle
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+
+    /// Device information captured at enumeration
+    pub fn info(&self) -> &PciDeviceInfo {
+        &self.info
+    }
+
+    fn config_read(&self, offset: u16, width: usize) -> Result<u32> {
+        let mut value: u32 = 0;
+        unsafe {
+            check(syscall4(
+                SyscallNumber::PciConfigRead as u64,
+                self.handle.raw() as u64,
+                offset as u64,
+                width as u64,
+                &mut value as *mut u32 as u64,
+            ))?;
+        }
+        Ok(value)
+    }
+
+    fn config_write(&self, offset: u16, width: usize, value: u32) -> Result<()> {
+        unsafe {
+            check(syscall4(
+                SyscallNumber::PciConfigWrite as u64,
+                self.handle.raw() as u64,
+                offset as u64,
+                width as u64,
+                value as u64,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Read a byte of configuration space
+    pub fn config_read8(&self, offset: u16) -> Result<u8> {
+        self.config_read(offset, 1).map(|v| v as u8)
+    }
+
+    /// Read a word of configuration space
+    pub fn config_read16(&self, offset: u16) -> Result<u16> {
+        self.config_read(offset, 2).map(|v| v as u16)
+    }
+
+    /// Read a dword of configuration space
+    pub fn config_read32(&self, offset: u16) -> Result<u32> {
+        self.config_read(offset, 4)
+    }
+
+    /// Write a byte of configuration space
+    pub fn config_write8(&self, offset: u16, value: u8) -> Result<()> {
+        self.config_write(offset, 1, value as u32)
+    }
+
+    /// Write a word of configuration space
+    pub fn config_write16(&self, offset: u16, value: u16) -> Result<()> {
+        self.config_write(offset, 2, value as u32)
+    }
+
+    /// Write a dword of configuration space
+    pub fn config_write32(&self, offset: u16, value: u32) -> Result<()> {
+        self.config_write(offset, 4, value)
+    }
+
+    /// Enable or disable bus mastering (required for DMA and MSI)
+    pub fn enable_bus_master(&self, enable: bool) -> Result<()> {
+        unsafe {
+            check(syscall2(
+                SyscallNumber::PciEnableBusMaster as u64,
+                self.handle.raw() as u64,
+                enable as u64,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Get BAR `bar_num`
+    ///
+    /// MMIO BARs come with a physical VMO covering the BAR.
+    pub fn get_bar(&self, bar_num: u32) -> Result<(PciBar, Option<Vmo>)> {
+        let mut bar = PciBar::default();
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall4(
+                SyscallNumber::PciGetBar as u64,
+                self.handle.raw() as u64,
+                bar_num as u64,
+                &mut bar as *mut PciBar as u64,
+                &mut out as *mut u32 as u64,
+            ))?;
+
+            let vmo = if bar.bar_type == bar_type::MMIO {
+                Some(Vmo::from_handle(Handle::from_raw(out, Rights::all())))
+            } else {
+                None
+            };
+            Ok((bar, vmo))
+        }
+    }
+
+    /// Map MMIO BAR `bar_num`
+    ///
+    /// Fails with `Status::WrongType` for PIO BARs.
+    pub fn map_bar(&self, bar_num: u32) -> Result<MmioBuffer> {
+        match self.get_bar(bar_num)? {
+            (bar, Some(vmo)) => MmioBuffer::map(vmo, 0, bar.size as usize),
+            (_, None) => Err(Error::new(Status::WrongType)),
+        }
+    }
+
+    /// Get an interrupt object for interrupt `which` in the current mode
+    pub fn map_interrupt(&self, which: i32) -> Result<Interrupt> {
+        let mut out: u32 = 0;
+        unsafe {
+            check(syscall3(
+                SyscallNumber::PciMapInterrupt as u64,
+                self.handle.raw() as u64,
+                which as u64,
+                &mut out as *mut u32 as u64,
+            ))?;
+            Ok(Interrupt::from_handle(Handle::from_raw(out, Rights::all())))
+        }
+    }
+
+    /// Maximum number of interrupts supported in `mode`
+    pub fn query_irq_mode(&self, mode: u32) -> Result<u32> {
+        let mut max_irqs: u32 = 0;
+        unsafe {
+            check(syscall3(
+                SyscallNumber::PciQueryIrqMode as u64,
+                self.handle.raw() as u64,
+                mode as u64,
+                &mut max_irqs as *mut u32 as u64,
+            ))?;
+        }
+        Ok(max_irqs)
+    }
+
+    /// Switch to `mode` with `count` interrupts
+    pub fn set_irq_mode(&self, mode: u32, count: u32) -> Result<()> {
+        unsafe {
+            check(syscall3(
+                SyscallNumber::PciSetIrqMode as u64,
+                self.handle.raw() as u64,
+                mode as u64,
+                count as u64,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Pick the best interrupt mode the device supports and enable one
+    /// interrupt in it
+    pub fn configure_irq(&self) -> Result<u32> {
+        for mode in [irq_mode::MSI_X, irq_mode::MSI, irq_mode::LEGACY] {
+            if matches!(self.query_irq_mode(mode), Ok(n) if n > 0) {
+                self.set_irq_mode(mode, 1)?;
+                return Ok(mode);
+            }
+        }
+        Err(Error::new(Status::NotSupported))
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Device Lifecycle Protocol
+//!
+//! The device manager drives each bound device through its lifecycle by
+//! sending messages over the device's channel; the driver host answers
+//! every request with a `Reply` carrying the same transaction ID.
+//!
+//! # Wire Format
+//!
+//! All fields are little-endian. Every message starts with a 16-byte
+//! header:
+//!
+//! ```text
+//! +0  txid     u32   Transaction ID, echoed in the reply
+//! +4  ordinal  u32   Message kind
+//! +8  status   i32   Reply status (0 in requests)
+//! +12 reserved u32
+//! ```
+//!
+//! `Bind` is followed by a 12-byte `DeviceDescriptor` and carries the
+//! device handle as its only transferred handle. `Suspend` is followed by
+//! the target power state as a u32. `Open` is followed by the instance to
+//! open as a u32: 0 is the device itself, higher numbers are the child
+//! devices it publishes, such as partitions. Other requests have no body.
+//!
+//! A reply to `Open` carries the device class's info record (for example
+//! `BlockInfo`) as its body and the client ends of the class protocol as
+//! its handles; other replies are header-only.
+
+use libsys::{Error, Result, Status};
+
+/// Size of the message header in bytes
+pub const HEADER_SIZE: usize = 16;
+
+/// Size of an encoded `DeviceDescriptor` in bytes
+pub const DESCRIPTOR_SIZE: usize = 12;
+
+/// Largest reply body
+pub const MAX_BODY_SIZE: usize = 32;
+
+/// Largest encoded message
+pub const MAX_MESSAGE_SIZE: usize = HEADER_SIZE + MAX_BODY_SIZE;
+
+/// Bus protocols a device can be bound through
+pub mod bus_protocol {
+    /// PCI device; the bind handle is a PCI device handle
+    pub const PCI: u32 = 1;
+
+    /// Platform device described by firmware
+    pub const PLATFORM: u32 = 2;
+}
+
+/// Message kinds
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ordinal {
+    /// Bind a driver to a new device
+    Bind = 1,
+    /// Stop the device and release it
+    Unbind = 2,
+    /// Enter a low-power state
+    Suspend = 3,
+    /// Return to the running state
+    Resume = 4,
+    /// Connect a client to the device's class protocol
+    Open = 5,
+    /// Reply to a request
+    Reply = 0x100,
+}
+
+impl Ordinal {
+    /// Convert from the raw wire value
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Ordinal::Bind),
+            2 => Some(Ordinal::Unbind),
+            3 => Some(Ordinal::Suspend),
+            4 => Some(Ordinal::Resume),
+            5 => Some(Ordinal::Open),
+            0x100 => Some(Ordinal::Reply),
+            _ => None,
+        }
+    }
+}
+
+/// Message header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageHeader {
+    /// Transaction ID
+    pub txid: u32,
+
+    /// Message kind
+    pub ordinal: Ordinal,
+
+    /// Reply status (0 = success)
+    pub status: i32,
+}
+
+/// Identity of a device offered for binding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceDescriptor {
+    /// `bus_protocol` the device is reached through
+    pub protocol: u32,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub base_class: u8,
+    pub sub_class: u8,
+    pub prog_if: u8,
+    pub revision_id: u8,
+}
+
+/// Lifecycle message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Bind a driver to `DeviceDescriptor`
+    Bind(DeviceDescriptor),
+    /// Unbind the driver
+    Unbind,
+    /// Suspend into the given power state
+    Suspend(u32),
+    /// Resume from suspend
+    Resume,
+    /// Open a client connection to an instance
+    Open(u32),
+    /// Reply to a request
+    Reply,
+}
+
+impl Message {
+    /// Ordinal of this message
+    pub fn ordinal(&self) -> Ordinal {
+        match self {
+            Message::Bind(_) => Ordinal::Bind,
+            Message::Unbind => Ordinal::Unbind,
+            Message::Suspend(_) => Ordinal::Suspend,
+            Message::Resume => Ordinal::Resume,
+            Message::Open(_) => Ordinal::Open,
+            Message::Reply => Ordinal::Reply,
+        }
+    }
+
+    /// Encode into `buf`, returning the number of bytes written
+    pub fn encode(&self, txid: u32, status: i32, buf: &mut [u8]) -> Result<usize> {
+        let len = HEADER_SIZE
+            + match self {
+                Message::Bind(_) => DESCRIPTOR_SIZE,
+                Message::Suspend(_) | Message::Open(_) => 4,
+                _ => 0,
+            };
+        if buf.len() < len {
+            return Err(Error::new(Status::BufferTooSmall));
+        }
+
+        put_u32(buf, 0, txid);
+        put_u32(buf, 4, self.ordinal() as u32);
+        put_u32(buf, 8, status as u32);
+        put_u32(buf, 12, 0);
+
+        match self {
+            Message::Bind(desc) => {
+                let body = &mut buf[HEADER_SIZE..];
+                put_u32(body, 0, desc.protocol);
+                put_u16(body, 4, desc.vendor_id);
+                put_u16(body, 6, desc.device_id);
+                body[8] = desc.base_class;
+                body[9] = desc.sub_class;
+                body[10] = desc.prog_if;
+                body[11] = desc.revision_id;
+            }
+            Message::Suspend(state) => put_u32(buf, HEADER_SIZE, *state),
+            Message::Open(instance) => put_u32(buf, HEADER_SIZE, *instance),
+            _ => {}
+        }
+
+        Ok(len)
+    }
+
+    /// Decode a message from `buf`
+    pub fn decode(buf: &[u8]) -> Result<(MessageHeader, Message)> {
+        if buf.len() < HEADER_SIZE {
+            return Err(Error::new(Status::BufferTooSmall));
+        }
+
+        let ordinal = Ordinal::from_raw(get_u32(buf, 4)).ok_or(Error::new(Status::NotSupported))?;
+        let header = MessageHeader {
+            txid: get_u32(buf, 0),
+            ordinal,
+            status: get_u32(buf, 8) as i32,
+        };
+
+        let body = &buf[HEADER_SIZE..];
+        let message = match ordinal {
+            Ordinal::Bind => {
+                if body.len() < DESCRIPTOR_SIZE {
+                    return Err(Error::new(Status::InvalidArgs));
+                }
+                Message::Bind(DeviceDescriptor {
+                    protocol: get_u32(body, 0),
+                    vendor_id: get_u16(body, 4),
+                    device_id: get_u16(body, 6),
+                    base_class: body[8],
+                    sub_class: body[9],
+                    prog_if: body[10],
+                    revision_id: body[11],
+                })
+            }
+            Ordinal::Suspend => {
+                if body.len() < 4 {
+                    return Err(Error::new(Status::InvalidArgs));
+                }
+                Message::Suspend(get_u32(body, 0))
+            }
+            Ordinal::Unbind => Message::Unbind,
+            Ordinal::Resume => Message::Resume,
+            Ordinal::Open => {
+                if body.len() < 4 {
+                    return Err(Error::new(Status::InvalidArgs));
+                }
+                Message::Open(get_u32(body, 0))
+            }
+            Ordinal::Reply => Message::Reply,
+        };
+
+        Ok((header, message))
+    }
+}
+
+fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_roundtrip() {
+        let desc = DeviceDescriptor {
+            protocol: bus_protocol::PCI,
+            vendor_id: 0x1af4,
+            device_id: 0x1041,
+            base_class: 0x02,
+            sub_class: 0x00,
+            prog_if: 0x00,
+            revision_id: 0x01,
+        };
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        let len = Message::Bind(desc).encode(7, 0, &mut buf).unwrap();
+        assert_eq!(len, HEADER_SIZE + DESCRIPTOR_SIZE);
+
+        let (header, message) = Message::decode(&buf[..len]).unwrap();
+        assert_eq!(header.txid, 7);
+        assert_eq!(header.ordinal, Ordinal::Bind);
+        assert_eq!(message, Message::Bind(desc));
+    }
+
+    #[test]
+    fn test_reply_status() {
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        let len = Message::Reply.encode(3, -2, &mut buf).unwrap();
+        assert_eq!(len, HEADER_SIZE);
+
+        let (header, message) = Message::decode(&buf[..len]).unwrap();
+        assert_eq!(header.status, -2);
+        assert_eq!(message, Message::Reply);
+    }
+
+    #[test]
+    fn test_open_roundtrip() {
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        let len = Message::Open(2).encode(9, 0, &mut buf).unwrap();
+        assert_eq!(len, HEADER_SIZE + 4);
+
+        let (_, message) = Message::decode(&buf[..len]).unwrap();
+        assert_eq!(message, Message::Open(2));
+        assert!(Message::decode(&buf[..HEADER_SIZE]).is_err());
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        assert!(Message::decode(&buf[..8]).is_err());
+
+        // Truncated Suspend body
+        let len = Message::Suspend(3).encode(1, 0, &mut buf).unwrap();
+        assert!(Message::decode(&buf[..len - 1]).is_err());
+
+        // Unknown ordinal
+        put_u32(&mut buf, 4, 0x55);
+        assert!(Message::decode(&buf).is_err());
+    }
+}
@@ -10,6 +10,7 @@
 
 #![no_std]
 
+use alloc::vec::Vec;
 use libsys::{Handle, Result, Status, Error, syscall::SyscallNumber};
 
 /// Arguments for reading from a channel
@@ -40,18 +41,61 @@ pub struct ChannelWriteArgs {
     pub handles_count: usize,
 }
 
-/// Arguments for channel call (write + wait for read)
+/// Arguments for `rx_channel_call_etc` (write + wait for the reply)
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct ChannelCallEtcArgs {
-    /// Pointer to write args
-    pub wr_args: *const ChannelWriteArgs,
-    /// Pointer to read args
-    pub rd_args: *mut ChannelReadArgs,
-    /// Deadline for the call
-    pub deadline: u64,
+    /// Request bytes; the kernel writes the transaction ID over the first 4
+    pub wr_bytes: *const u8,
+    /// Request handle dispositions
+    pub wr_handles: *const HandleDisposition,
+    /// Buffer for the reply bytes
+    pub rd_bytes: *mut u8,
+    /// Buffer for the reply's handles
+    pub rd_handles: *mut HandleInfo,
+    /// Size of the request
+    pub wr_num_bytes: u32,
+    /// Number of request handles
+    pub wr_num_handles: u32,
+    /// Capacity of the reply buffer
+    pub rd_num_bytes: u32,
+    /// Capacity of the reply handle buffer
+    pub rd_num_handles: u32,
 }
 
+/// How a call sends one handle
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandleDisposition {
+    /// 0 to move the handle, 1 to send a duplicate
+    pub operation: u32,
+    /// Handle value
+    pub handle: u32,
+    /// Expected object type, 0 for any
+    pub obj_type: u32,
+    /// Rights to send, or `Rights::SAME_RIGHTS`
+    pub rights: u32,
+    /// Written back: this handle's status
+    pub result: i32,
+}
+
+/// A handle received in a reply
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandleInfo {
+    /// Handle value
+    pub handle: u32,
+    /// Object type
+    pub obj_type: u32,
+    /// Rights
+    pub rights: u32,
+    /// Reserved
+    pub unused: u32,
+}
+
+/// Most handles a message carries
+const MAX_HANDLES: usize = 64;
+
 /// Channel endpoint
 ///
 /// Channels are bidirectional message pipes that support:
@@ -59,7 +103,7 @@ pub struct ChannelCallEtcArgs {
 /// - Transferring handles along with messages
 /// - Waiting for messages with timeouts
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Channel {
     handle: Handle,
 }
@@ -72,8 +116,8 @@ impl Channel {
         Handle::duplicate(&Handle::INVALID, libsys::Rights::all()).and_then(|_| {
             let (ch_a, ch_b) = libsys::Channel::create()?;
             Ok((
-                Self { handle: *ch_a.handle() },
-                Self { handle: *ch_b.handle() },
+                Self { handle: ch_a.into_handle() },
+                Self { handle: ch_b.into_handle() },
             ))
         })
     }
@@ -92,6 +136,11 @@ impl Channel {
         &self.handle
     }
 
+    /// Take the underlying handle
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+
     /// Write data to the channel
     ///
     /// # Arguments
@@ -189,17 +238,20 @@ impl Channel {
         }
     }
 
-    /// Perform a channel call (write then wait for read)
+    /// Perform a channel call (write then wait for the reply)
     ///
-    /// This is a combined operation that writes a message and waits for a response.
+    /// The kernel gives the request a transaction ID in its first 4 bytes
+    /// and returns the first message back that carries it. While the
+    /// server handles the request it runs at no less than this thread's
+    /// priority. The handles are moved to the server.
     ///
     /// # Arguments
     ///
-    /// * `write_bytes` - Data to send
+    /// * `write_bytes` - Request, at least 4 bytes
     /// * `write_handles` - Handles to transfer
     /// * `read_bytes` - Buffer for response data
     /// * `read_handles` - Vector for received handles
-    /// * `deadline` - Optional deadline (0 = no deadline)
+    /// * `deadline` - Monotonic deadline (0 = no deadline)
     pub fn call(
         &self,
         write_bytes: &[u8],
@@ -213,69 +265,50 @@ impl Channel {
             return Err(Error::new(Status::AccessDenied));
         }
 
-        let wr_args = ChannelWriteArgs {
-            bytes: write_bytes.as_ptr(),
-            bytes_size: write_bytes.len(),
-            handles: write_handles.as_ptr(),
-            handles_count: write_handles.len(),
+        let dispositions: Vec<HandleDisposition> = write_handles
+            .iter()
+            .map(|handle| HandleDisposition {
+                operation: 0,
+                handle: handle.raw(),
+                obj_type: 0,
+                rights: libsys::Rights::SAME_RIGHTS.bits(),
+                result: 0,
+            })
+            .collect();
+        let mut infos = [HandleInfo::default(); MAX_HANDLES];
+
+        let args = ChannelCallEtcArgs {
+            wr_bytes: write_bytes.as_ptr(),
+            wr_handles: dispositions.as_ptr(),
+            rd_bytes: read_bytes.as_mut_ptr(),
+            rd_handles: infos.as_mut_ptr(),
+            wr_num_bytes: write_bytes.len() as u32,
+            wr_num_handles: dispositions.len() as u32,
+            rd_num_bytes: read_bytes.len() as u32,
+            rd_num_handles: infos.len() as u32,
         };
 
-        // First call to get sizes
-        let mut handle_count: u32 = 0;
-        let mut byte_count: u64 = 0;
-
-        unsafe {
-            let ret = libsys::syscall::syscall6(
+        let ret = unsafe {
+            libsys::syscall::syscall4(
                 SyscallNumber::ChannelCallEtc as u64,
                 self.handle.raw() as u64,
                 0, // options
-                &wr_args as *const ChannelWriteArgs as u64,
-                0, // rd_args pointer (null to get size)
-                deadline,
-                &mut byte_count as *mut u64 as u64,
-                &mut handle_count as *mut u32 as u64,
-            );
-
-            if (ret as i32) < 0 && (ret as i32) != Status::BufferTooSmall as i32 {
-                return Err(Error::from_raw(ret as i32));
-            }
-        }
-
-        // Allocate space for handles
-        if handle_count > 0 {
-            read_handles.reserve(handle_count as usize);
-            read_handles.set_len(handle_count as usize);
-        }
-
-        let rd_args = ChannelReadArgs {
-            bytes: read_bytes.as_mut_ptr(),
-            bytes_size: read_bytes.len(),
-            handles: if read_handles.is_empty() {
-                core::ptr::null_mut()
-            } else {
-                read_handles.as_mut_ptr()
-            },
-            handles_capacity: read_handles.len(),
+                if deadline == 0 { u64::MAX } else { deadline },
+                &args as *const ChannelCallEtcArgs as u64,
+            )
         };
+        if (ret as i64) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
 
-        unsafe {
-            let ret = libsys::syscall::syscall6(
-                SyscallNumber::ChannelCallEtc as u64,
-                self.handle.raw() as u64,
-                0, // options
-                &wr_args as *const ChannelWriteArgs as u64,
-                &rd_args as *mut ChannelReadArgs as u64,
-                deadline,
-                &mut byte_count as *mut u64 as u64,
-                &mut handle_count as *mut u32 as u64,
-            );
-
-            if (ret as i32) < 0 {
-                return Err(Error::from_raw(ret as i32));
-            }
-
-            Ok(byte_count as usize)
+        // Low 32 bits are bytes, high 32 bits handles
+        let num_handles = (ret >> 32) as usize;
+        read_handles.clear();
+        for info in &infos[..num_handles.min(MAX_HANDLES)] {
+            let rights = libsys::Rights::from_bits_truncate(info.rights);
+            read_handles.push(unsafe { Handle::from_raw(info.handle, rights) });
         }
+        Ok(ret as u32 as usize)
     }
 
     /// Query for incoming message size
@@ -8,10 +8,15 @@
 //!
 //! Events are simple synchronization primitives that can be signaled
 //! and waited upon. EventPairs provide bidirectional signaling.
+//!
+//! Both use user signal 0: signaling sets it and resetting clears it.
 
 #![no_std]
 
+use core::mem::ManuallyDrop;
+
 use libsys::{Handle, Result, Error, Status, syscall::SyscallNumber};
+use libsys::handles::SIGNAL_USER_0;
 
 /// Event object
 ///
@@ -20,7 +25,7 @@ use libsys::{Handle, Result, Error, Status, syscall::SyscallNumber};
 /// - Unsignaled (reset)
 /// - Waited upon
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Event {
     handle: Handle,
 }
@@ -32,11 +37,11 @@ impl Event {
     ///
     /// * `initial` - Whether the event starts signaled
     pub fn create(initial: bool) -> Result<Self> {
-        let h = libsys::Event::create()?;
+        let event = Self { handle: libsys::Event::create()?.into_handle() };
         if initial {
-            h.signal()?;
+            event.signal()?;
         }
-        Ok(Self { handle: *h.handle() })
+        Ok(event)
     }
 
     /// Create an event from a raw handle
@@ -53,37 +58,32 @@ impl Event {
         &self.handle
     }
 
+    /// Take the underlying handle
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+
     /// Signal the event
     pub fn signal(&self) -> Result<()> {
-        if !self.handle.rights().contains(libsys::Rights::SIGNAL) {
-            return Err(Error::new(Status::AccessDenied));
-        }
-
-        unsafe {
-            let ret = libsys::syscall::syscall1(
-                SyscallNumber::ObjectSignal as u64,
-                self.handle.raw() as u64,
-            );
-
-            if (ret as i32) < 0 {
-                return Err(Error::from_raw(ret as i32));
-            }
-
-            Ok(())
-        }
+        self.update(0, SIGNAL_USER_0)
     }
 
     /// Reset the event
     pub fn reset(&self) -> Result<()> {
+        self.update(SIGNAL_USER_0, 0)
+    }
+
+    fn update(&self, clear_mask: u64, set_mask: u64) -> Result<()> {
         if !self.handle.rights().contains(libsys::Rights::SIGNAL) {
             return Err(Error::new(Status::AccessDenied));
         }
 
         unsafe {
-            let ret = libsys::syscall::syscall2(
+            let ret = libsys::syscall::syscall3(
                 SyscallNumber::ObjectSignal as u64,
                 self.handle.raw() as u64,
-                0, // options to reset
+                clear_mask,
+                set_mask,
             );
 
             if (ret as i32) < 0 {
@@ -124,9 +124,10 @@ impl Event {
 /// EventPair object
 ///
 /// EventPairs provide bidirectional signaling between two parties.
-/// Each endpoint can signal and wait on its peer.
+/// Each endpoint can signal and wait on its peer. Once the peer's last
+/// handle closes, `signal_peer` fails with `PeerClosed`.
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct EventPair {
     handle: Handle,
 }
@@ -136,8 +137,8 @@ impl EventPair {
     pub fn create() -> Result<(Self, Self)> {
         let (ep_a, ep_b) = libsys::EventPair::create()?;
         Ok((
-            Self { handle: *ep_a.handle() },
-            Self { handle: *ep_b.handle() },
+            Self { handle: ep_a.into_handle() },
+            Self { handle: ep_b.into_handle() },
         ))
     }
 
@@ -155,24 +156,26 @@ impl EventPair {
         &self.handle
     }
 
+    /// Take the underlying handle
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+
     /// Signal the peer
     pub fn signal_peer(&self) -> Result<()> {
-        if !self.handle.rights().contains(libsys::Rights::SIGNAL_PEER) {
-            return Err(Error::new(Status::AccessDenied));
-        }
-
-        unsafe {
-            let ret = libsys::syscall::syscall1(
-                SyscallNumber::ObjectSignalPeer as u64,
-                self.handle.raw() as u64,
-            );
+        self.pair().signal_peer(0, SIGNAL_USER_0)
+    }
 
-            if (ret as i32) < 0 {
-                return Err(Error::from_raw(ret as i32));
-            }
+    /// Clear a signal the peer sent, before waiting for the next one
+    pub fn reset(&self) -> Result<()> {
+        self.pair().signal(SIGNAL_USER_0, 0)
+    }
 
-            Ok(())
-        }
+    /// Borrow our handle as a libsys event pair, without taking ownership
+    fn pair(&self) -> ManuallyDrop<libsys::EventPair> {
+        ManuallyDrop::new(unsafe {
+            libsys::EventPair::from_handle(Handle::from_raw(self.handle.raw(), self.handle.rights()))
+        })
     }
 
     /// Wait for the peer to signal
@@ -0,0 +1,135 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! FIFOs
+//!
+//! A FIFO pair is a bidirectional queue of fixed-size elements. Elements
+//! written to one end are read from the other. FIFOs are meant for small
+//! `#[repr(C)]` records such as I/O requests; bulk data travels in a
+//! shared VMO that the records point into.
+
+use libsys::{Handle, Result, Rights, Error, Status, syscall::SyscallNumber};
+
+/// Kernel status for an empty (read) or full (write) FIFO
+const RX_ERR_SHOULD_WAIT: i32 = -18;
+
+/// FIFO endpoint
+#[repr(C)]
+#[derive(Debug)]
+pub struct Fifo {
+    handle: Handle,
+}
+
+impl Fifo {
+    /// Create a FIFO pair holding up to `count` elements of `elem_size`
+    /// bytes in each direction
+    pub fn create(count: usize, elem_size: usize) -> Result<(Self, Self)> {
+        unsafe {
+            let mut out0: u64 = 0;
+            let mut out1: u64 = 0;
+
+            let ret = libsys::syscall::syscall5(
+                SyscallNumber::FifoCreate as u64,
+                count as u64,
+                elem_size as u64,
+                0, // options
+                &mut out0 as *mut u64 as u64,
+                &mut out1 as *mut u64 as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok((
+                Self {
+                    handle: Handle::from_raw(out0 as u32, Rights::all()),
+                },
+                Self {
+                    handle: Handle::from_raw(out1 as u32, Rights::all()),
+                },
+            ))
+        }
+    }
+
+    /// Create a FIFO from a raw handle
+    ///
+    /// # Safety
+    ///
+    /// The handle must be a valid FIFO handle.
+    pub unsafe fn from_handle(handle: Handle) -> Self {
+        Self { handle }
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Take the underlying handle
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+
+    /// Write elements to the peer
+    ///
+    /// Returns how many elements were queued, 0 if the peer's queue is
+    /// full. `T` must match the element size the FIFO was created with.
+    pub fn write<T: Copy>(&self, elems: &[T]) -> Result<usize> {
+        if !self.handle.rights().contains(Rights::WRITE) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = libsys::syscall::syscall5(
+                SyscallNumber::FifoWrite as u64,
+                self.handle.raw() as u64,
+                core::mem::size_of::<T>() as u64,
+                elems.as_ptr() as u64,
+                elems.len() as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) == RX_ERR_SHOULD_WAIT {
+                return Ok(0);
+            }
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(actual)
+    }
+
+    /// Read elements written by the peer
+    ///
+    /// Returns how many elements were read, 0 if none are queued.
+    pub fn read<T: Copy>(&self, elems: &mut [T]) -> Result<usize> {
+        if !self.handle.rights().contains(Rights::READ) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = libsys::syscall::syscall5(
+                SyscallNumber::FifoRead as u64,
+                self.handle.raw() as u64,
+                core::mem::size_of::<T>() as u64,
+                elems.as_mut_ptr() as u64,
+                elems.len() as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) == RX_ERR_SHOULD_WAIT {
+                return Ok(0);
+            }
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(actual)
+    }
+}
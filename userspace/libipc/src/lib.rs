@@ -9,7 +9,10 @@
 //! This library provides high-level wrappers for IPC primitives:
 //! - Channels for message passing
 //! - Events and EventPairs for signaling
+//! - FIFOs for fixed-size element queues
 //! - Ports for packet delivery
+//! - Shared-memory rings for zero-copy message streams
+//! - Method-call protocols generated by `#[ipc_protocol]`
 //!
 //! # Examples
 //!
@@ -37,11 +40,18 @@
 
 #![no_std]
 
+extern crate alloc;
+
 pub mod channel;
 pub mod event;
+pub mod fifo;
 pub mod port;
+pub mod protocol;
+pub mod ring;
 
 // Re-export commonly used types
-pub use channel::{Channel, ChannelReadArgs, ChannelWriteArgs, ChannelCallEtcArgs};
+pub use channel::{Channel, ChannelReadArgs, ChannelWriteArgs, ChannelCallEtcArgs, HandleDisposition, HandleInfo};
 pub use event::{Event, EventPair};
+pub use fifo::Fifo;
 pub use port::{Port, Packet, PacketWaitResult};
+pub use ring::{Ring, Producer, Consumer};
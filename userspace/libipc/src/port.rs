@@ -80,7 +80,7 @@ pub struct PacketWaitResult {
 /// Ports provide a queue-based IPC mechanism for delivering packets
 /// and notifications from multiple sources.
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Port {
     handle: Handle,
 }
@@ -94,7 +94,7 @@ impl Port {
     pub fn create(capacity: usize) -> Result<Self> {
         let h = libsys::Port::create()?;
         // TODO: Set capacity if needed
-        Ok(Self { handle: *h.handle() })
+        Ok(Self { handle: h.into_handle() })
     }
 
     /// Create a port from a raw handle
@@ -111,6 +111,11 @@ impl Port {
         &self.handle
     }
 
+    /// Take the underlying handle
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+
     /// Queue a packet to the port
     ///
     /// # Arguments
@@ -0,0 +1,498 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Channel Protocols
+//!
+//! Runtime for method-call protocols declared with `#[ipc_protocol]` from
+//! `rustux_macros`. A protocol is a trait whose methods are numbered by
+//! ordinal; the macro generates a client proxy that turns each call into a
+//! request over a channel, and a server wrapper implementing [`Dispatch`]
+//! that decodes requests and calls the trait.
+//!
+//! ```ignore
+//! #[ipc_protocol(version = 2)]
+//! pub trait Block {
+//!     #[ordinal(1)]
+//!     fn info(&mut self) -> Result<BlockInfo>;
+//!     #[ordinal(2)]
+//!     fn read(&mut self, block: u64, count: u32, vmo: Handle) -> Result<u32>;
+//!     #[ordinal(3, since = 2)]
+//!     fn flush(&mut self) -> Result<()>;
+//! }
+//!
+//! // Client
+//! let mut block = BlockProxy::new(channel);
+//! let info = block.info()?;
+//!
+//! // Server
+//! let mut server = BlockServer(driver);
+//! protocol::serve(&mut server, &channel, &request, &handles)?;
+//! ```
+//!
+//! # Wire Format
+//!
+//! All fields are little-endian. Every message starts with a 16-byte
+//! header:
+//!
+//! ```text
+//! +0  txid     u32   Transaction ID, set by the kernel, echoed in the reply
+//! +4  ordinal  u32   Method number, echoed in the reply
+//! +8  status   i32   Reply status (0 in requests)
+//! +12 version  u32   Protocol version of the sender
+//! ```
+//!
+//! A request's body is its arguments in declaration order; a successful
+//! reply's body is the return value, and a failed reply has no body.
+//! Integers are encoded at their natural width, `bool` as one byte, and a
+//! `Vec<T>` as a u32 count followed by the elements. A handle is a u32
+//! index into the handles transferred with the message.
+//!
+//! # Versions
+//!
+//! A method marked `since = N` was added in version `N`. A proxy told that
+//! its peer speaks an older version fails such calls with `NotSupported`
+//! without sending them; a server answers ordinals it doesn't know the
+//! same way.
+
+use alloc::vec::Vec;
+use libsys::{Error, Handle, Result, Status};
+
+use crate::Channel;
+
+/// Size of the message header in bytes
+pub const HEADER_SIZE: usize = 16;
+
+/// Largest message a proxy accepts as a reply
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Largest number of handles a proxy accepts with a reply
+pub const MAX_HANDLES: usize = 64;
+
+/// Message header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageHeader {
+    /// Transaction ID
+    pub txid: u32,
+
+    /// Method number
+    pub ordinal: u32,
+
+    /// Reply status (0 = success)
+    pub status: i32,
+
+    /// Protocol version of the sender
+    pub version: u32,
+}
+
+/// Message being built
+#[derive(Debug, Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+    handles: Vec<Handle>,
+}
+
+impl Encoder {
+    /// Create an empty message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a message starting with `header`
+    pub fn with_header(header: MessageHeader) -> Self {
+        let mut enc = Self::new();
+        header.encode(&mut enc);
+        enc
+    }
+
+    /// Append raw bytes
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Attach a handle, returning its index
+    pub fn put_handle(&mut self, handle: Handle) -> u32 {
+        self.handles.push(handle);
+        (self.handles.len() - 1) as u32
+    }
+
+    /// Create a message of `header` followed by `body`
+    ///
+    /// The header carries no handles, so handle indices in `body` stay
+    /// valid.
+    pub fn with_body(header: MessageHeader, body: Encoder) -> Self {
+        let mut enc = Self::with_header(header);
+        enc.bytes.extend_from_slice(&body.bytes);
+        enc.handles = body.handles;
+        enc
+    }
+
+    /// Encoded bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Attached handles
+    pub fn handles(&self) -> &[Handle] {
+        &self.handles
+    }
+
+    /// Split into the encoded bytes and the attached handles
+    pub fn into_parts(self) -> (Vec<u8>, Vec<Handle>) {
+        (self.bytes, self.handles)
+    }
+
+    /// Give up the attached handles once the kernel has moved them to the
+    /// peer, so dropping the message doesn't close them
+    fn forget_handles(&mut self) {
+        for handle in self.handles.drain(..) {
+            core::mem::forget(handle);
+        }
+    }
+}
+
+/// Message being read
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    handles: &'a mut [Handle],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// Read a message of `bytes` that arrived with `handles`
+    ///
+    /// Decoding a handle takes it out of `handles`; the ones left behind
+    /// are closed with the caller's buffer.
+    pub fn new(bytes: &'a [u8], handles: &'a mut [Handle]) -> Self {
+        Self { bytes, handles, pos: 0 }
+    }
+
+    /// Take the next `len` bytes
+    pub fn take_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() - self.pos < len {
+            return Err(Error::new(Status::BufferTooSmall));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Take the handle at `index` in the message's handles
+    ///
+    /// Each handle can be taken once; asking for it again fails with
+    /// `InvalidArgs`.
+    pub fn handle(&mut self, index: u32) -> Result<Handle> {
+        let slot = self.handles.get_mut(index as usize).ok_or(Error::new(Status::InvalidArgs))?;
+        if !slot.is_valid() {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        Ok(core::mem::replace(slot, Handle::INVALID))
+    }
+
+    /// Check that the whole message was read
+    pub fn finish(&self) -> Result<()> {
+        if self.pos != self.bytes.len() {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        Ok(())
+    }
+}
+
+/// A type that can travel in a protocol message
+///
+/// Implemented for integers, `bool`, `()`, [`Handle`] and `Vec<T>`;
+/// `#[derive(Wire)]` from `rustux_macros` implements it for structs.
+pub trait Wire: Sized {
+    /// Append `self` to `enc`, moving any handles it holds into the message
+    fn encode(self, enc: &mut Encoder);
+
+    /// Read a value from `dec`
+    fn decode(dec: &mut Decoder) -> Result<Self>;
+}
+
+macro_rules! wire_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Wire for $ty {
+                fn encode(self, enc: &mut Encoder) {
+                    enc.put_bytes(&self.to_le_bytes());
+                }
+
+                fn decode(dec: &mut Decoder) -> Result<Self> {
+                    let bytes = dec.take_bytes(core::mem::size_of::<$ty>())?;
+                    Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+wire_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Wire for bool {
+    fn encode(self, enc: &mut Encoder) {
+        (self as u8).encode(enc);
+    }
+
+    fn decode(dec: &mut Decoder) -> Result<Self> {
+        match u8::decode(dec)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::new(Status::InvalidArgs)),
+        }
+    }
+}
+
+impl Wire for () {
+    fn encode(self, _enc: &mut Encoder) {}
+
+    fn decode(_dec: &mut Decoder) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl Wire for Handle {
+    fn encode(self, enc: &mut Encoder) {
+        let index = enc.put_handle(self);
+        index.encode(enc);
+    }
+
+    fn decode(dec: &mut Decoder) -> Result<Self> {
+        let index = u32::decode(dec)?;
+        dec.handle(index)
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode(self, enc: &mut Encoder) {
+        (self.len() as u32).encode(enc);
+        for item in self {
+            item.encode(enc);
+        }
+    }
+
+    fn decode(dec: &mut Decoder) -> Result<Self> {
+        let count = u32::decode(dec)? as usize;
+        // Every element takes at least a byte; don't trust the count further
+        if count > dec.bytes.len() - dec.pos {
+            return Err(Error::new(Status::BufferTooSmall));
+        }
+        (0..count).map(|_| T::decode(dec)).collect()
+    }
+}
+
+impl Wire for MessageHeader {
+    fn encode(self, enc: &mut Encoder) {
+        self.txid.encode(enc);
+        self.ordinal.encode(enc);
+        self.status.encode(enc);
+        self.version.encode(enc);
+    }
+
+    fn decode(dec: &mut Decoder) -> Result<Self> {
+        Ok(Self {
+            txid: u32::decode(dec)?,
+            ordinal: u32::decode(dec)?,
+            status: i32::decode(dec)?,
+            version: u32::decode(dec)?,
+        })
+    }
+}
+
+/// Client end of a protocol
+///
+/// Generated proxies wrap one of these. Each method call is a channel
+/// call, so the kernel numbers transactions and matches replies to
+/// requests.
+pub struct Client {
+    channel: Channel,
+    version: u32,
+}
+
+impl Client {
+    /// Talk over `channel` using protocol `version`
+    pub fn new(channel: Channel, version: u32) -> Self {
+        Self { channel, version }
+    }
+
+    /// The underlying channel
+    pub fn channel(&self) -> &Channel {
+        &self.channel
+    }
+
+    /// Protocol version requests are sent with
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Call method `ordinal`, added in version `since`, with encoded `args`
+    ///
+    /// # Errors
+    ///
+    /// - `NotSupported` - the method is newer than the client's version,
+    ///   or the server doesn't implement it
+    /// - `BadState` - the reply doesn't match the request
+    /// - any error the server's method returned
+    pub fn call<R: Wire>(&mut self, ordinal: u32, since: u32, args: Encoder) -> Result<R> {
+        if since > self.version {
+            return Err(Error::new(Status::NotSupported));
+        }
+
+        // The kernel fills in the transaction ID
+        let header = MessageHeader { txid: 0, ordinal, status: 0, version: self.version };
+        let mut request = Encoder::with_body(header, args);
+
+        let mut reply = alloc::vec![0u8; MAX_MESSAGE_SIZE];
+        let mut handles = Vec::with_capacity(MAX_HANDLES);
+        let len = self.channel.call(request.bytes(), request.handles(), &mut reply, &mut handles, 0)?;
+        request.forget_handles();
+
+        let mut dec = Decoder::new(&reply[..len], &mut handles);
+        let header = MessageHeader::decode(&mut dec)?;
+        if header.ordinal != ordinal {
+            return Err(Error::new(Status::BadState));
+        }
+        if header.status != 0 {
+            return Err(Error::from_raw(header.status));
+        }
+
+        let value = R::decode(&mut dec)?;
+        dec.finish()?;
+        Ok(value)
+    }
+}
+
+/// Server end of a protocol
+///
+/// Implemented by the server wrapper `#[ipc_protocol]` generates.
+pub trait Dispatch {
+    /// Protocol version the server speaks
+    const VERSION: u32;
+
+    /// Decode the arguments of method `ordinal` from `args`, call it, and
+    /// encode its return value
+    fn dispatch(&mut self, ordinal: u32, args: &mut Decoder) -> Result<Encoder>;
+}
+
+/// Build the reply to the request in `bytes` and `handles`
+///
+/// Handles the method takes are moved out of `handles`.
+pub fn handle_request<D: Dispatch>(server: &mut D, bytes: &[u8], handles: &mut [Handle]) -> Result<Encoder> {
+    let mut dec = Decoder::new(bytes, handles);
+    let request = MessageHeader::decode(&mut dec)?;
+
+    let result = server.dispatch(request.ordinal, &mut dec);
+    let status = match &result {
+        Ok(_) => 0,
+        Err(e) => e.status().into_raw(),
+    };
+
+    let header = MessageHeader {
+        txid: request.txid,
+        ordinal: request.ordinal,
+        status,
+        version: D::VERSION,
+    };
+    Ok(Encoder::with_body(header, result.unwrap_or_default()))
+}
+
+/// Answer the request in `bytes` and `handles`, read from `channel`
+///
+/// Fails only if the request has no valid header or the reply can't be
+/// written; errors from the method itself are sent to the client.
+pub fn serve<D: Dispatch>(server: &mut D, channel: &Channel, bytes: &[u8], handles: &mut [Handle]) -> Result<()> {
+    let mut reply = handle_request(server, bytes, handles)?;
+    channel.write(reply.bytes(), reply.handles())?;
+    reply.forget_handles();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server with a single method, `add(a, b)`, at ordinal 1
+    struct Adder;
+
+    impl Dispatch for Adder {
+        const VERSION: u32 = 1;
+
+        fn dispatch(&mut self, ordinal: u32, args: &mut Decoder) -> Result<Encoder> {
+            match ordinal {
+                1 => {
+                    let a = u32::decode(args)?;
+                    let b = u32::decode(args)?;
+                    args.finish()?;
+                    let mut reply = Encoder::new();
+                    a.checked_add(b).ok_or(Error::new(Status::InvalidArgs))?.encode(&mut reply);
+                    Ok(reply)
+                }
+                _ => Err(Error::new(Status::NotSupported)),
+            }
+        }
+    }
+
+    fn request(ordinal: u32, args: &[u32]) -> Encoder {
+        let mut enc = Encoder::with_header(MessageHeader { txid: 5, ordinal, status: 0, version: 1 });
+        for &arg in args {
+            arg.encode(&mut enc);
+        }
+        enc
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let handle = unsafe { Handle::from_raw(42, libsys::Rights::all()) };
+        let mut enc = Encoder::new();
+        7u16.encode(&mut enc);
+        true.encode(&mut enc);
+        alloc::vec![1u64, 2, 3].encode(&mut enc);
+        handle.encode(&mut enc);
+        (-3i32).encode(&mut enc);
+        assert_eq!(enc.bytes().len(), 2 + 1 + 4 + 24 + 4 + 4);
+
+        let (bytes, mut handles) = enc.into_parts();
+        let mut dec = Decoder::new(&bytes, &mut handles);
+        assert_eq!(u16::decode(&mut dec).unwrap(), 7);
+        assert!(bool::decode(&mut dec).unwrap());
+        assert_eq!(Vec::<u64>::decode(&mut dec).unwrap(), alloc::vec![1, 2, 3]);
+        let handle = Handle::decode(&mut dec).unwrap();
+        assert_eq!(handle.raw(), 42);
+        assert_eq!(i32::decode(&mut dec).unwrap(), -3);
+        assert!(dec.finish().is_ok());
+        assert!(u8::decode(&mut dec).is_err());
+
+        // The handle was moved out; it can't be taken twice
+        assert_eq!(dec.handle(0).unwrap_err().status(), Status::InvalidArgs);
+        assert!(!handles[0].is_valid());
+        core::mem::forget(handle);
+    }
+
+    #[test]
+    fn test_dispatch_reply() {
+        let req = request(1, &[2, 3]);
+        let reply = handle_request(&mut Adder, req.bytes(), &mut []).unwrap();
+
+        let mut dec = Decoder::new(reply.bytes(), &mut []);
+        let header = MessageHeader::decode(&mut dec).unwrap();
+        assert_eq!(header, MessageHeader { txid: 5, ordinal: 1, status: 0, version: 1 });
+        assert_eq!(u32::decode(&mut dec).unwrap(), 5);
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_dispatch_errors() {
+        // Unknown ordinal, trailing argument, method failure: header only
+        for req in [request(9, &[]), request(1, &[2, 3, 4]), request(1, &[u32::MAX, 1])] {
+            let reply = handle_request(&mut Adder, req.bytes(), &mut []).unwrap();
+            assert_eq!(reply.bytes().len(), HEADER_SIZE);
+            let header = MessageHeader::decode(&mut Decoder::new(reply.bytes(), &mut [])).unwrap();
+            assert_ne!(header.status, 0);
+        }
+
+        // No header at all
+        assert!(handle_request(&mut Adder, &[0; 8], &mut []).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Shared-Memory Rings
+//!
+//! A ring is a single-producer single-consumer message queue in a VMO
+//! that both ends map. Messages are copied straight into shared memory, so
+//! a send or receive makes no syscall unless the other end is asleep.
+//!
+//! The VMO holds a header page followed by `capacity` bytes of data:
+//!
+//! ```text
+//! 0x0000  magic, capacity
+//! 0x0040  head  - bytes ever written, advanced by the producer
+//! 0x0080  tail  - bytes ever read, advanced by the consumer
+//! 0x00C0  consumer_waiting
+//! 0x0100  producer_waiting
+//! 0x1000  data
+//! ```
+//!
+//! `head` and `tail` are free-running 32-bit counters on their own cache
+//! lines; the data offset of either is the counter masked by the capacity.
+//! Each message is a 32-bit length followed by its bytes, padded to 4
+//! bytes. A record's payload may wrap around the end of the data area.
+//!
+//! An end that finds the ring empty (or full) sets its waiting flag and
+//! waits on the futex at `head` (or `tail`); the other end only makes a
+//! wake syscall when it sees the flag set.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let ring = Ring::create(64 * 1024)?;
+//! let peer = ring.vmo().handle().duplicate(Rights::SAME_RIGHTS)?;
+//! // ... send `peer` to the other process, which calls Ring::attach ...
+//!
+//! let mut producer = ring.into_producer();
+//! producer.send(b"request")?;
+//! ```
+
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+use libsys::{Error, Result, Status, Vmo, vmar, syscall::SyscallNumber};
+
+/// "RING"
+const RING_MAGIC: u32 = 0x474e_4952;
+
+/// Size of the header page before the data
+const HEADER_SIZE: usize = 4096;
+
+/// Length prefix of each record
+const RECORD_HEADER: usize = 4;
+
+/// Mapping permissions
+const MAP_PERM_READ: u32 = 0x01;
+const MAP_PERM_WRITE: u32 = 0x02;
+
+/// Smallest and largest data area
+pub const MIN_CAPACITY: usize = 64;
+pub const MAX_CAPACITY: usize = 1 << 30;
+
+/// A counter alone on its cache line
+#[repr(C, align(64))]
+struct CacheLine {
+    value: AtomicU32,
+}
+
+/// Start of the header page
+#[repr(C)]
+struct Header {
+    magic: AtomicU32,
+    capacity: AtomicU32,
+    head: CacheLine,
+    tail: CacheLine,
+    consumer_waiting: CacheLine,
+    producer_waiting: CacheLine,
+}
+
+/// Bytes a message of `len` bytes takes in the ring
+fn record_size(len: usize) -> usize {
+    (RECORD_HEADER + len + 3) & !3
+}
+
+/// Wait until the futex word at `word` no longer holds `expected`
+fn futex_wait(word: &AtomicU32, expected: u32) {
+    unsafe {
+        libsys::syscall::syscall2(
+            SyscallNumber::FutexWait as u64,
+            word as *const AtomicU32 as u64,
+            expected as u64,
+        );
+    }
+}
+
+/// Wake the waiter on the futex word at `word`
+fn futex_wake(word: &AtomicU32) {
+    unsafe {
+        libsys::syscall::syscall1(SyscallNumber::FutexWake as u64, word as *const AtomicU32 as u64);
+    }
+}
+
+/// A mapped ring
+///
+/// Turn it into a [`Producer`] or [`Consumer`] to use it; each end of the
+/// ring must be used by one thread at a time.
+pub struct Ring {
+    vmo: Vmo,
+    addr: usize,
+    len: usize,
+    capacity: usize,
+}
+
+impl Ring {
+    /// Create a ring with `capacity` bytes of data
+    ///
+    /// `capacity` must be a power of two between [`MIN_CAPACITY`] and
+    /// [`MAX_CAPACITY`].
+    pub fn create(capacity: usize) -> Result<Self> {
+        if !capacity.is_power_of_two() || capacity < MIN_CAPACITY || capacity > MAX_CAPACITY {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let len = HEADER_SIZE + capacity;
+        let vmo = Vmo::create(len as u64, Some("ipc-ring"))?;
+        let ring = Self::map(vmo, len, capacity)?;
+
+        // A fresh VMO is zeroed; the magic goes in last so that a peer
+        // attaching early sees either nothing or a complete header
+        let header = ring.header();
+        header.capacity.store(capacity as u32, Ordering::Relaxed);
+        header.magic.store(RING_MAGIC, Ordering::Release);
+
+        Ok(ring)
+    }
+
+    /// Map a ring another process created
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidArgs` - `vmo` doesn't hold a ring
+    pub fn attach(vmo: Vmo) -> Result<Self> {
+        let size = vmo.get_size()? as usize;
+        if size < HEADER_SIZE + MIN_CAPACITY {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let mut ring = Self::map(vmo, size, 0)?;
+        let header = ring.header();
+        if header.magic.load(Ordering::Acquire) != RING_MAGIC {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let capacity = header.capacity.load(Ordering::Relaxed) as usize;
+        if !capacity.is_power_of_two() || capacity < MIN_CAPACITY || HEADER_SIZE + capacity > size {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        ring.capacity = capacity;
+        Ok(ring)
+    }
+
+    /// Map the first `len` bytes of `vmo`
+    fn map(vmo: Vmo, len: usize, capacity: usize) -> Result<Self> {
+        let root = vmar::root_self()?;
+        let addr = vmar::map(&root, 0, &vmo, 0, len, MAP_PERM_READ | MAP_PERM_WRITE)?;
+        Ok(Self { vmo, addr, len, capacity })
+    }
+
+    /// The VMO backing the ring, to share with the other end
+    pub fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// Bytes of message data the ring holds
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Largest message the ring can carry
+    pub fn max_message(&self) -> usize {
+        self.capacity - RECORD_HEADER
+    }
+
+    /// Use this mapping as the sending end
+    pub fn into_producer(self) -> Producer {
+        Producer { ring: self }
+    }
+
+    /// Use this mapping as the receiving end
+    pub fn into_consumer(self) -> Consumer {
+        Consumer { ring: self }
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.addr as *const Header) }
+    }
+
+    fn data(&self) -> *mut u8 {
+        (self.addr + HEADER_SIZE) as *mut u8
+    }
+
+    /// Copy `src` into the data area at counter `pos`, wrapping at the end
+    fn write_at(&self, pos: u32, src: &[u8]) {
+        let offset = pos as usize & (self.capacity - 1);
+        let first = src.len().min(self.capacity - offset);
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), self.data().add(offset), first);
+            core::ptr::copy_nonoverlapping(src.as_ptr().add(first), self.data(), src.len() - first);
+        }
+    }
+
+    /// Copy from the data area at counter `pos` into `dst`, wrapping at the end
+    fn read_at(&self, pos: u32, dst: &mut [u8]) {
+        let offset = pos as usize & (self.capacity - 1);
+        let first = dst.len().min(self.capacity - offset);
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.data().add(offset), dst.as_mut_ptr(), first);
+            core::ptr::copy_nonoverlapping(self.data(), dst.as_mut_ptr().add(first), dst.len() - first);
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        if let Ok(root) = vmar::root_self() {
+            let _ = vmar::unmap(&root, self.addr, self.len);
+        }
+    }
+}
+
+/// Sending end of a ring
+pub struct Producer {
+    ring: Ring,
+}
+
+impl Producer {
+    /// The underlying ring
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// Queue `msg` without blocking
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidArgs` - `msg` is larger than [`Ring::max_message`]
+    /// - `WouldBlock` - the ring doesn't have room for `msg`
+    pub fn try_send(&mut self, msg: &[u8]) -> Result<()> {
+        if msg.len() > self.ring.max_message() {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let header = self.ring.header();
+        let head = header.head.value.load(Ordering::Relaxed);
+        let tail = header.tail.value.load(Ordering::Acquire);
+        let size = record_size(msg.len());
+        if self.ring.capacity - (head.wrapping_sub(tail) as usize) < size {
+            return Err(Error::new(Status::WouldBlock));
+        }
+
+        // Records are 4-byte aligned, so the length prefix never wraps
+        self.ring.write_at(head, &(msg.len() as u32).to_le_bytes());
+        self.ring.write_at(head.wrapping_add(RECORD_HEADER as u32), msg);
+        header.head.value.store(head.wrapping_add(size as u32), Ordering::Release);
+
+        // Order the head store before the flag load; pairs with the fence
+        // in Consumer::recv
+        fence(Ordering::SeqCst);
+        if header.consumer_waiting.value.load(Ordering::Relaxed) != 0 {
+            futex_wake(&header.head.value);
+        }
+        Ok(())
+    }
+
+    /// Queue `msg`, waiting for room if the ring is full
+    pub fn send(&mut self, msg: &[u8]) -> Result<()> {
+        loop {
+            match self.try_send(msg) {
+                Err(e) if e.status() == Status::WouldBlock => {}
+                result => return result,
+            }
+
+            let header = self.ring.header();
+            let tail = header.tail.value.load(Ordering::Relaxed);
+            header.producer_waiting.value.store(1, Ordering::Relaxed);
+            fence(Ordering::SeqCst);
+
+            // Re-check after announcing the wait, so a read that raced
+            // with the flag isn't missed
+            if header.tail.value.load(Ordering::Acquire) == tail {
+                futex_wait(&header.tail.value, tail);
+            }
+            header.producer_waiting.value.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Receiving end of a ring
+pub struct Consumer {
+    ring: Ring,
+}
+
+impl Consumer {
+    /// The underlying ring
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// Size of the next message, if there is one
+    pub fn peek_len(&self) -> Option<usize> {
+        let header = self.ring.header();
+        let tail = header.tail.value.load(Ordering::Relaxed);
+        if header.head.value.load(Ordering::Acquire) == tail {
+            return None;
+        }
+        let mut len = [0u8; RECORD_HEADER];
+        self.ring.read_at(tail, &mut len);
+        Some(u32::from_le_bytes(len) as usize)
+    }
+
+    /// Take the next message into `buf` without blocking
+    ///
+    /// Returns the message length.
+    ///
+    /// # Errors
+    ///
+    /// - `WouldBlock` - the ring is empty
+    /// - `BufferTooSmall` - the next message doesn't fit in `buf`; it
+    ///   stays in the ring
+    pub fn try_recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.peek_len().ok_or(Error::new(Status::WouldBlock))?;
+        if len > buf.len() {
+            return Err(Error::new(Status::BufferTooSmall));
+        }
+
+        let header = self.ring.header();
+        let tail = header.tail.value.load(Ordering::Relaxed);
+        self.ring.read_at(tail.wrapping_add(RECORD_HEADER as u32), &mut buf[..len]);
+        header.tail.value.store(tail.wrapping_add(record_size(len) as u32), Ordering::Release);
+
+        fence(Ordering::SeqCst);
+        if header.producer_waiting.value.load(Ordering::Relaxed) != 0 {
+            futex_wake(&header.tail.value);
+        }
+        Ok(len)
+    }
+
+    /// Take the next message into `buf`, waiting if the ring is empty
+    pub fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            match self.try_recv(buf) {
+                Err(e) if e.status() == Status::WouldBlock => {}
+                result => return result,
+            }
+
+            let header = self.ring.header();
+            let tail = header.tail.value.load(Ordering::Relaxed);
+            header.consumer_waiting.value.store(1, Ordering::Relaxed);
+            fence(Ordering::SeqCst);
+
+            if header.head.value.load(Ordering::Acquire) == tail {
+                futex_wait(&header.head.value, tail);
+            }
+            header.consumer_waiting.value.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_size() {
+        assert_eq!(record_size(0), 4);
+        assert_eq!(record_size(1), 8);
+        assert_eq!(record_size(4), 8);
+        assert_eq!(record_size(5), 12);
+    }
+
+    #[test]
+    fn test_header_layout() {
+        assert_eq!(core::mem::offset_of!(Header, head), 0x40);
+        assert_eq!(core::mem::offset_of!(Header, tail), 0x80);
+        assert_eq!(core::mem::offset_of!(Header, consumer_waiting), 0xC0);
+        assert_eq!(core::mem::offset_of!(Header, producer_waiting), 0x100);
+        assert!(core::mem::size_of::<Header>() <= HEADER_SIZE);
+    }
+}
@@ -7,15 +7,20 @@
 //! Condition variable implementation
 //!
 //! This module provides condition variables for thread synchronization.
+//!
+//! A condvar is a futex word holding a sequence number. Waiters read it
+//! while still holding the mutex, release the mutex and sleep on the futex
+//! until the value changes. Notifiers bump the sequence before waking, so
+//! a notify that lands between a waiter's unlock and its futex wait makes
+//! the wait return at once instead of being lost.
 
 #![no_std]
 
 use core::sync::atomic::{AtomicU32, Ordering};
-use libsys::{Result, Error, Status, syscall::SyscallNumber};
 
-/// Condition variable state
-const CONDVAR_NO_WAITERS: u32 = 0;
-const CONDVAR_HAS_WAITERS: u32 = 1;
+use crate::futex;
+use crate::mutex::MutexGuard;
+use crate::timer::get_monotonic_time;
 
 /// Result from waiting on a condition variable
 #[repr(C)]
@@ -32,11 +37,11 @@ pub enum WaitResult {
 /// A condition variable
 ///
 /// Condition variables allow threads to block until a condition is met.
+/// Wake-ups may be spurious, so waiters should re-check their condition
+/// in a loop or use [`Condvar::wait_while`].
 #[repr(C)]
 pub struct Condvar {
-    /// Internal state
-    state: AtomicU32,
-    /// Sequence counter for tracking wake-ups
+    /// Sequence counter, bumped by every notify
     sequence: AtomicU32,
 }
 
@@ -48,7 +53,6 @@ impl Condvar {
     /// Create a new condition variable
     pub const fn new() -> Self {
         Self {
-            state: AtomicU32::new(CONDVAR_NO_WAITERS),
             sequence: AtomicU32::new(0),
         }
     }
@@ -60,142 +64,78 @@ impl Condvar {
     ///
     /// # Arguments
     ///
-    /// * `mutex` - The mutex to release while waiting
-    pub fn wait(&self, mutex: &super::mutex::Mutex) {
-        self.wait_with(mutex, || true)
+    /// * `guard` - Guard for the mutex to release while waiting
+    pub fn wait<'a>(&self, guard: MutexGuard<'a>) -> MutexGuard<'a> {
+        self.wait_until(guard, futex::INFINITE).0
     }
 
     /// Wait on the condition variable with a timeout
     ///
+    /// The mutex is re-acquired before returning, even on timeout.
+    ///
     /// # Arguments
     ///
-    /// * `mutex` - The mutex to release while waiting
+    /// * `guard` - Guard for the mutex to release while waiting
     /// * `nanos` - Timeout in nanoseconds
-    pub fn wait_timeout(&self, mutex: &super::mutex::Mutex, nanos: u64) -> WaitResult {
-        self.wait_with_timeout(mutex, || true, nanos)
+    pub fn wait_timeout<'a>(&self, guard: MutexGuard<'a>, nanos: u64) -> (MutexGuard<'a>, WaitResult) {
+        let deadline = get_monotonic_time().saturating_add(nanos);
+        self.wait_until(guard, deadline)
     }
 
     /// Wait while a condition is true
     ///
-    /// This function will continue waiting as long as the condition
-    /// callback returns true.
+    /// The condition is checked with the mutex held, before the first wait
+    /// and after every wake-up.
     ///
     /// # Arguments
     ///
-    /// * `mutex` - The mutex to release while waiting
+    /// * `guard` - Guard for the mutex to release while waiting
     /// * `condition` - Function that returns true to continue waiting
-    pub fn wait_while(&self, mutex: &super::mutex::Mutex, condition: impl Fn() -> bool) {
-        self.wait_with(mutex, condition)
-    }
-
-    /// Internal wait implementation
-    fn wait_with(&self, mutex: &super::mutex::Mutex, mut condition: impl Fn() -> bool) {
-        // Increment sequence
-        let seq = self.sequence.fetch_add(1, Ordering::Acquire);
-
-        loop {
-            // Release mutex while waiting
-            unsafe { mutex.unlock() };
-
-            // Wait on the futex
-            unsafe {
-                libsys::syscall::syscall2(
-                    SyscallNumber::FutexWait as u64,
-                    &self.sequence as *const AtomicU32 as u64,
-                    seq as u64,
-                );
-            }
-
-            // Re-acquire mutex
-            mutex.lock();
-
-            // Check if condition is satisfied
-            if !condition() {
-                break;
-            }
-        }
-    }
-
-    /// Internal wait implementation with timeout
-    fn wait_with_timeout(
+    pub fn wait_while<'a>(
         &self,
-        mutex: &super::mutex::Mutex,
-        condition: impl Fn() -> bool,
-        nanos: u64,
-    ) -> WaitResult {
-        // Increment sequence
-        let seq = self.sequence.fetch_add(1, Ordering::Acquire);
-
-        // Calculate deadline
-        let start = self.get_time();
-        let deadline = start.saturating_add(nanos);
-
-        loop {
-            // Release mutex while waiting
-            unsafe { mutex.unlock() };
-
-            // Wait on the futex with timeout
-            unsafe {
-                libsys::syscall::syscall3(
-                    SyscallNumber::FutexWait as u64,
-                    &self.sequence as *const AtomicU32 as u64,
-                    seq as u64,
-                    deadline,
-                );
-            }
-
-            // Re-acquire mutex
-            mutex.lock();
-
-            // Check if condition is satisfied
-            if !condition() {
-                return WaitResult::Success;
-            }
-
-            // Check for timeout
-            let now = self.get_time();
-            if now >= deadline {
-                return WaitResult::TimedOut;
-            }
+        mut guard: MutexGuard<'a>,
+        mut condition: impl FnMut() -> bool,
+    ) -> MutexGuard<'a> {
+        while condition() {
+            guard = self.wait(guard);
         }
+        guard
     }
 
-    /// Get the current time in nanoseconds
-    #[inline]
-    fn get_time(&self) -> u64 {
-        // TODO: Use VDSO clock_get_monotonic when available
-        // For now, use a simple counter
-        0
+    /// Internal wait implementation
+    ///
+    /// `deadline` is in monotonic nanoseconds.
+    fn wait_until<'a>(&self, guard: MutexGuard<'a>, deadline: u64) -> (MutexGuard<'a>, WaitResult) {
+        // Read the sequence before unlocking; the mutex orders it against
+        // notifiers that changed the condition under the same lock
+        let seq = self.sequence.load(Ordering::Relaxed);
+        let mutex = MutexGuard::unlock(guard);
+
+        let woken = futex::wait(&self.sequence, seq, deadline);
+
+        let guard = mutex.relock();
+        if woken {
+            (guard, WaitResult::Success)
+        } else {
+            (guard, WaitResult::TimedOut)
+        }
     }
 
     /// Signal one waiting thread
     ///
     /// If any threads are waiting on this condition variable, one will be woken.
     pub fn notify_one(&self) {
-        self.sequence.fetch_add(1, Ordering::Release);
-
-        unsafe {
-            libsys::syscall::syscall2(
-                SyscallNumber::FutexWake as u64,
-                &self.sequence as *const AtomicU32 as u64,
-                1, // wake one
-            );
-        }
+        self.sequence.fetch_add(1, Ordering::Relaxed);
+        futex::wake(&self.sequence, 1);
     }
 
     /// Signal all waiting threads
     ///
-    /// All threads waiting on this condition variable will be woken.
+    /// All threads waiting on this condition variable will be woken. They
+    /// re-acquire the mutex one at a time.
     pub fn notify_all(&self) {
-        self.sequence.fetch_add(1, Ordering::Release);
-
-        unsafe {
-            libsys::syscall::syscall2(
-                SyscallNumber::FutexWake as u64,
-                &self.sequence as *const AtomicU32 as u64,
-                u32::MAX as u64, // wake all
-            );
-        }
+        self.sequence.fetch_add(1, Ordering::Relaxed);
+        futex::wake(&self.sequence, futex::WAKE_ALL);
     }
 }
 
@@ -204,3 +144,82 @@ impl Default for Condvar {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutex::Mutex;
+    use core::sync::atomic::{AtomicBool, AtomicUsize};
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct Shared {
+        mutex: Mutex,
+        condvar: Condvar,
+        ready: AtomicBool,
+        woken: AtomicUsize,
+    }
+
+    /// Start a thread that waits for `ready`, then counts itself woken
+    fn spawn_waiter(shared: &Arc<Shared>) -> thread::JoinHandle<()> {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            let guard = shared.mutex.lock();
+            let _guard = shared.condvar.wait_while(guard, || !shared.ready.load(Ordering::Relaxed));
+            shared.woken.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    fn set_ready(shared: &Shared) {
+        let _guard = shared.mutex.lock();
+        shared.ready.store(true, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_timed_out_wait_relocks() {
+        let mutex = Mutex::new();
+        let condvar = Condvar::new();
+
+        let (guard, result) = condvar.wait_until(mutex.lock(), 0);
+        assert_eq!(result, WaitResult::TimedOut);
+        assert!(mutex.try_lock().is_err());
+        drop(guard);
+        assert!(mutex.try_lock().is_ok());
+    }
+
+    #[test]
+    fn test_notify_before_sleep_is_not_lost() {
+        let condvar = Condvar::new();
+
+        // A waiter read the sequence, then a notify landed before it slept
+        let seq = condvar.sequence.load(Ordering::Relaxed);
+        condvar.notify_one();
+        assert!(futex::wait(&condvar.sequence, seq, 0));
+    }
+
+    #[test]
+    fn test_notify_one_wakes_waiter() {
+        let shared = Arc::new(Shared::default());
+        let waiter = spawn_waiter(&shared);
+
+        set_ready(&shared);
+        shared.condvar.notify_one();
+        waiter.join().unwrap();
+        assert_eq!(shared.woken.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_notify_all_wakes_every_waiter() {
+        let shared = Arc::new(Shared::default());
+        let waiters: Vec<_> = (0..4).map(|_| spawn_waiter(&shared)).collect();
+
+        set_ready(&shared);
+        shared.condvar.notify_all();
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(shared.woken.load(Ordering::Relaxed), 4);
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Futex helpers
+//!
+//! Thin wrappers over the futex syscalls shared by [`Mutex`](crate::Mutex)
+//! and [`Condvar`](crate::Condvar).
+//!
+//! Host unit tests run without a kernel, so under `cfg(test)` waits poll
+//! the word instead and wakes do nothing.
+
+#![no_std]
+
+use core::sync::atomic::AtomicU32;
+#[cfg(test)]
+use core::sync::atomic::Ordering;
+#[cfg(not(test))]
+use libsys::syscall::SyscallNumber;
+
+/// Deadline that never expires
+pub(crate) const INFINITE: u64 = u64::MAX;
+
+/// Wake count that wakes every waiter
+pub(crate) const WAKE_ALL: u32 = 0;
+
+/// Kernel status for an expired deadline
+#[cfg(not(test))]
+const ERR_TIMED_OUT: i64 = -7;
+
+/// Sleep while `word` holds `value`
+///
+/// Returns at once if `word` no longer holds `value`, so a wake that lands
+/// between reading `word` and calling this is never lost. Wake-ups may be
+/// spurious; callers re-check their condition.
+///
+/// Returns false if `deadline` (monotonic nanoseconds) passed first.
+#[cfg(not(test))]
+pub(crate) fn wait(word: &AtomicU32, value: u32, deadline: u64) -> bool {
+    let ret = unsafe {
+        libsys::syscall::syscall4(
+            SyscallNumber::FutexWait as u64,
+            word as *const AtomicU32 as u64,
+            value as u64,
+            0, // no owner
+            deadline,
+        )
+    };
+    ret as i64 != ERR_TIMED_OUT
+}
+
+/// Host stand-in for `wait`: yields until `word` changes
+///
+/// There is no kernel clock to compare against, so a finite `deadline`
+/// counts as already passed.
+#[cfg(test)]
+pub(crate) fn wait(word: &AtomicU32, value: u32, deadline: u64) -> bool {
+    while word.load(Ordering::Acquire) == value {
+        if deadline != INFINITE {
+            return false;
+        }
+        std::thread::yield_now();
+    }
+    true
+}
+
+/// Wake up to `count` threads sleeping on `word`, or all with [`WAKE_ALL`]
+#[cfg(not(test))]
+pub(crate) fn wake(word: &AtomicU32, count: u32) {
+    unsafe {
+        libsys::syscall::syscall2(
+            SyscallNumber::FutexWake as u64,
+            word as *const AtomicU32 as u64,
+            count as u64,
+        );
+    }
+}
+
+/// Host stand-in for `wake`: pollers see the word change by themselves
+#[cfg(test)]
+pub(crate) fn wake(_word: &AtomicU32, _count: u32) {}
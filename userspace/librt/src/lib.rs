@@ -9,6 +9,7 @@
 //! This library provides runtime support for userspace programs:
 //! - Thread creation and management
 //! - Synchronization primitives (Mutex, Condvar)
+//! - One-time initialization (Once, OnceCell, Lazy)
 //! - Timer helpers
 //!
 //! # Examples
@@ -35,13 +36,20 @@
 
 #![no_std]
 
+#[cfg(test)]
+extern crate std;
+
 pub mod thread;
 pub mod mutex;
+mod futex;
 pub mod condvar;
+// Lives in libsys, which uses it for its own globals
+pub use libsys::once;
 pub mod timer;
 
 // Re-export commonly used types
 pub use thread::{Thread, ThreadBuilder};
 pub use mutex::{Mutex, MutexGuard};
 pub use condvar::{Condvar, WaitResult};
+pub use once::{Lazy, Once, OnceCell, OnceState};
 pub use timer::{Timer, TimerId};
@@ -12,7 +12,9 @@
 
 use core::cell::UnsafeCell;
 use core::sync::atomic::{AtomicU32, Ordering};
-use libsys::{Result, Error, Status, syscall::SyscallNumber};
+use libsys::{Result, Error, Status};
+
+use crate::futex;
 
 /// Mutex state values
 const MUTEX_UNLOCKED: u32 = 0;
@@ -44,47 +46,36 @@ impl Mutex {
     /// Acquire the mutex
     ///
     /// This function will block until the mutex is available.
-    pub fn lock(&self) {
+    pub fn lock(&self) -> MutexGuard<'_> {
         // Try to acquire the lock
         if self
             .state
             .compare_exchange(MUTEX_UNLOCKED, MUTEX_LOCKED, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+            .is_err()
         {
-            return;
+            // Lock is contended, use futex to wait
+            self.lock_contended();
         }
 
-        // Lock is contended, use futex to wait
+        MutexGuard { mutex: self }
+    }
+
+    /// Acquire the mutex after sleeping on a condvar
+    ///
+    /// Other threads woken with this one may already be queued on the
+    /// mutex, so it is taken in the contended state to make the next unlock
+    /// wake them.
+    pub(crate) fn relock(&self) -> MutexGuard<'_> {
         self.lock_contended();
+        MutexGuard { mutex: self }
     }
 
     #[cold]
     fn lock_contended(&self) {
-        loop {
-            // Try to acquire again
-            if self
-                .state
-                .compare_exchange(MUTEX_UNLOCKED, MUTEX_LOCKED, Ordering::Acquire, Ordering::Relaxed)
-                .is_ok()
-            {
-                return;
-            }
-
-            // Set state to contended if it's just locked
-            if self
-                .state
-                .compare_exchange(MUTEX_LOCKED, MUTEX_CONTENDED, Ordering::Relaxed, Ordering::Relaxed)
-                .is_ok()
-            {
-                // Wait on the futex
-                unsafe {
-                    libsys::syscall::syscall2(
-                        SyscallNumber::FutexWait as u64,
-                        &self.state as *const AtomicU32 as u64,
-                        MUTEX_CONTENDED as u64,
-                    );
-                }
-            }
+        // Whoever takes the lock from here leaves it contended, since other
+        // threads may still be sleeping on it
+        while self.state.swap(MUTEX_CONTENDED, Ordering::Acquire) != MUTEX_UNLOCKED {
+            futex::wait(&self.state, MUTEX_CONTENDED, futex::INFINITE);
         }
     }
 
@@ -119,10 +110,7 @@ impl Mutex {
             == MUTEX_CONTENDED
         {
             // Wake one waiter
-            libsys::syscall::syscall1(
-                SyscallNumber::FutexWake as u64,
-                &self.state as *const AtomicU32 as u64,
-            );
+            futex::wake(&self.state, 1);
         }
     }
 }
@@ -149,6 +137,14 @@ impl<'a> MutexGuard<'a> {
     unsafe fn new(mutex: &'a Mutex) -> Self {
         Self { mutex }
     }
+
+    /// Release the mutex without dropping the borrow, so it can be re-locked
+    pub(crate) fn unlock(guard: Self) -> &'a Mutex {
+        let mutex = guard.mutex;
+        core::mem::forget(guard);
+        unsafe { mutex.unlock() };
+        mutex
+    }
 }
 
 impl<'a> Drop for MutexGuard<'a> {
@@ -198,7 +194,7 @@ impl ReentrantMutex {
             self.set_count(self.count() + 1);
         } else {
             // Different thread, lock the underlying mutex
-            self.mutex.lock();
+            core::mem::forget(self.mutex.lock());
             self.set_owner(current_id);
             self.set_count(1);
         }
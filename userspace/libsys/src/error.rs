@@ -66,6 +66,9 @@ pub enum Status {
 
     /// Wrong type for handle
     WrongType = 15,
+
+    /// The other end of a paired object has closed
+    PeerClosed = 16,
 }
 
 impl Status {
@@ -88,6 +91,7 @@ impl Status {
             13 => Status::Busy,
             14 => Status::Internal,
             15 => Status::WrongType,
+            16 => Status::PeerClosed,
             _ => Status::Internal,
         }
     }
@@ -127,6 +131,7 @@ impl fmt::Display for Status {
             Status::Busy => write!(f, "Resource busy"),
             Status::Internal => write!(f, "Internal error"),
             Status::WrongType => write!(f, "Wrong type"),
+            Status::PeerClosed => write!(f, "Peer closed"),
         }
     }
 }
@@ -196,5 +201,8 @@ mod tests {
 
         let error = Error::from_raw(7);
         assert_eq!(error.status(), Status::AccessDenied);
+
+        let error = Error::from_raw(16);
+        assert_eq!(error.status(), Status::PeerClosed);
     }
 }
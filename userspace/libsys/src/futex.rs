@@ -0,0 +1,66 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Futex helpers
+//!
+//! The futex syscalls [`Once`](crate::once::Once) sleeps and wakes with.
+//! librt has its own copy for its locks, since it is built on top of
+//! libsys.
+//!
+//! Host unit tests run without a kernel, so under `cfg(test)` waits poll
+//! the word instead and wakes do nothing.
+
+#![no_std]
+
+use core::sync::atomic::AtomicU32;
+#[cfg(test)]
+use core::sync::atomic::Ordering;
+#[cfg(not(test))]
+use crate::syscall::{syscall2, syscall4, SyscallNumber};
+
+/// Wake count that wakes every waiter
+pub(crate) const WAKE_ALL: u32 = 0;
+
+/// Sleep while `word` holds `value`
+///
+/// Returns at once if `word` no longer holds `value`. Wake-ups may be
+/// spurious; callers re-check their condition.
+#[cfg(not(test))]
+pub(crate) fn wait(word: &AtomicU32, value: u32) {
+    unsafe {
+        syscall4(
+            SyscallNumber::FutexWait as u64,
+            word as *const AtomicU32 as u64,
+            value as u64,
+            0, // no owner
+            u64::MAX, // no deadline
+        );
+    }
+}
+
+/// Host stand-in for `wait`: yields until `word` changes
+#[cfg(test)]
+pub(crate) fn wait(word: &AtomicU32, value: u32) {
+    while word.load(Ordering::Acquire) == value {
+        std::thread::yield_now();
+    }
+}
+
+/// Wake up to `count` threads sleeping on `word`, or all with [`WAKE_ALL`]
+#[cfg(not(test))]
+pub(crate) fn wake(word: &AtomicU32, count: u32) {
+    unsafe {
+        syscall2(
+            SyscallNumber::FutexWake as u64,
+            word as *const AtomicU32 as u64,
+            count as u64,
+        );
+    }
+}
+
+/// Host stand-in for `wake`: pollers see the word change by themselves
+#[cfg(test)]
+pub(crate) fn wake(_word: &AtomicU32, _count: u32) {}
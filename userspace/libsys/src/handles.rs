@@ -14,52 +14,42 @@
 
 use bitflags::bitflags;
 use crate::error::{Error, Result, Status};
-use crate::syscall::{SyscallNumber, syscall1, syscall2, syscall3, syscall4};
+use crate::syscall::{SyscallNumber, syscall1, syscall2, syscall3, syscall4, syscall5, syscall6};
 
 bitflags! {
     /// Rights that can be held on a handle
+    ///
+    /// Bit values are the kernel's, so a mask can be passed straight to
+    /// `rx_handle_duplicate` and friends.
     #[repr(C)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub struct Rights: u64 {
-        /// Transfer handle (duplicate)
-        const TRANSFER = 1 << 0;
-        /// Duplicate handle
-        const DUPLICATE = 1 << 1;
-        /// Basic operation
-        const READ = 1 << 2;
-        /// Write operation
-        const WRITE = 1 << 3;
-        /// Execute operation
-        const EXECUTE = 1 << 4;
-        /// Map memory
-        const MAP = 1 << 5;
-        /// Get property
-        const GET_PROPERTY = 1 << 6;
-        /// Set property
-        const SET_PROPERTY = 1 << 7;
-        /// Enumerate
-        const ENUMERATE = 1 << 8;
-        /// Destroy
-        const DESTROY = 1 << 9;
-        /// Set policy
-        const SET_POLICY = 1 << 10;
-        /// Get policy
-        const GET_POLICY = 1 << 11;
+    pub struct Rights: u32 {
+        /// Read state
+        const READ = 0x01;
+        /// Modify state
+        const WRITE = 0x02;
+        /// Execute code
+        const EXECUTE = 0x04;
         /// Signal
-        const SIGNAL = 1 << 12;
-        /// Signal peer
-        const SIGNAL_PEER = 1 << 13;
-        /// Wait
-        const WAIT = 1 << 14;
-        /// All rights
-        const ALL = u64::MAX;
-        /// Same rights
-        const SAME_RIGHTS = 1 << 63;
-
-        // VMO-specific rights
-        /// Property rights
-        const PROP_VMO_CONTENT_SIZE = 1 << 20;
-        const PROP_VMO_CHILD = 1 << 21;
+        const SIGNAL = 0x08;
+        /// Wait (the kernel checks the same bit as `SIGNAL`)
+        const WAIT = 0x08;
+        /// Map into a VMAR
+        const MAP = 0x10;
+        /// Duplicate handle
+        const DUPLICATE = 0x20;
+        /// Transfer to another process
+        const TRANSFER = 0x40;
+        /// Admin control
+        const MANAGE = 0x80;
+        /// Apply profile to thread
+        const APPLY_PROFILE = 0x100;
+        /// Signal the peer of a paired object
+        const SIGNAL_PEER = 0x200;
+        /// Change the size of a VMO
+        const RESIZE = 0x400;
+        /// Keep the same rights on duplicate or replace
+        const SAME_RIGHTS = 0x8000_0000;
     }
 }
 
@@ -67,8 +57,13 @@ bitflags! {
 ///
 /// Handles are used to reference kernel objects. They are reference-counted
 /// and automatically closed when dropped.
+///
+/// A handle remembers the rights it was created with, so operations can
+/// fail with `AccessDenied` without a syscall. Use [`Handle::duplicate`]
+/// to get a second handle; handles are not `Copy`, since each one is
+/// closed exactly once.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Handle {
     /// Raw handle value
     raw: u32,
@@ -76,6 +71,9 @@ pub struct Handle {
     rights: Rights,
 }
 
+/// Most handles [`Handle::close_many`] accepts at once
+pub const HANDLE_CLOSE_MANY_MAX: usize = 1024;
+
 impl Handle {
     /// Invalid handle value (used for uninitialized handles)
     pub const INVALID: Self = Self {
@@ -109,55 +107,83 @@ impl Handle {
     }
 
     /// Duplicate this handle
+    ///
+    /// `rights` must be a subset of this handle's rights, or
+    /// [`Rights::SAME_RIGHTS`] to keep them all.
+    ///
+    /// # Errors
+    ///
+    /// - `AccessDenied` - this handle lacks [`Rights::DUPLICATE`]
+    /// - `InvalidArgs` - `rights` asks for a right this handle lacks
     pub fn duplicate(&self, rights: Rights) -> Result<Self> {
         if !self.rights.contains(Rights::DUPLICATE) {
             return Err(Error::new(Status::AccessDenied));
         }
+        let rights = self.reduced_rights(rights)?;
 
         unsafe {
+            let mut out: u32 = 0;
+
             let ret = syscall3(
                 SyscallNumber::HandleDuplicate as u64,
                 self.raw as u64,
-                if rights.contains(Rights::SAME_RIGHTS) {
-                    self.rights.bits()
-                } else {
-                    rights.bits()
-                },
-                0, // No new handle value returned, we get it from syscall result
+                rights.bits() as u64,
+                &mut out as *mut u32 as u64,
             );
 
             if (ret as i32) < 0 {
                 return Err(Error::from_raw(ret as i32));
             }
 
-            Ok(Self {
-                raw: ret as u32,
-                rights,
-            })
+            Ok(Self::from_raw(out, rights))
         }
     }
 
-    /// Replace this handle with a new one
-    pub fn replace(&mut self, new_handle: Self) -> Result<Self> {
-        if !self.rights.contains(Rights::DUPLICATE) {
-            return Err(Error::new(Status::AccessDenied));
-        }
-
-        unsafe {
-            let ret = syscall2(
+    /// Replace this handle with one holding fewer rights
+    ///
+    /// `rights` must be a subset of this handle's rights, or
+    /// [`Rights::SAME_RIGHTS`]. This handle is closed even if the call
+    /// fails.
+    pub fn replace(self, rights: Rights) -> Result<Self> {
+        let rights = self.reduced_rights(rights)?;
+
+        let mut out: u32 = 0;
+        let ret = unsafe {
+            syscall3(
                 SyscallNumber::HandleReplace as u64,
-                new_handle.raw as u64,
                 self.raw as u64,
-            );
+                rights.bits() as u64,
+                &mut out as *mut u32 as u64,
+            )
+        };
 
-            if (ret as i32) < 0 {
-                return Err(Error::from_raw(ret as i32));
-            }
+        // The kernel has consumed the old handle either way
+        core::mem::forget(self);
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
 
-            let old = *self;
-            self.raw = ret as u32;
-            self.rights = new_handle.rights;
-            Ok(old)
+        Ok(unsafe { Self::from_raw(out, rights) })
+    }
+
+    /// Resolve `rights` for a duplicate or replace of this handle
+    fn reduced_rights(&self, rights: Rights) -> Result<Rights> {
+        if rights.contains(Rights::SAME_RIGHTS) {
+            Ok(self.rights)
+        } else if self.rights.contains(rights) {
+            Ok(rights)
+        } else {
+            Err(Error::new(Status::InvalidArgs))
+        }
+    }
+
+    /// Fail with `AccessDenied` unless this handle holds all of `rights`
+    pub fn require(&self, rights: Rights) -> Result<()> {
+        if self.rights.contains(rights) {
+            Ok(())
+        } else {
+            Err(Error::new(Status::AccessDenied))
         }
     }
 
@@ -167,12 +193,48 @@ impl Handle {
             return Ok(());
         }
 
+        let ret = unsafe { syscall1(SyscallNumber::HandleClose as u64, self.raw as u64) };
+        // Closed (or never open); don't let drop close it again
+        core::mem::forget(self);
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(())
+    }
+
+    /// Close every handle in `handles`, or none of them
+    ///
+    /// Fails with `BadHandle`, closing nothing, if any valid entry is not
+    /// an open handle or appears twice. On success every entry is set to
+    /// [`Handle::INVALID`].
+    pub fn close_many(handles: &mut [Handle]) -> Result<()> {
+        if handles.len() > HANDLE_CLOSE_MANY_MAX {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        // The kernel skips 0, its invalid handle value
+        let mut raw = [0u32; HANDLE_CLOSE_MANY_MAX];
+        for (raw, handle) in raw.iter_mut().zip(handles.iter()) {
+            if handle.is_valid() {
+                *raw = handle.raw;
+            }
+        }
+
         unsafe {
-            let ret = syscall1(SyscallNumber::HandleClose as u64, self.raw as u64);
+            let ret = syscall2(
+                SyscallNumber::HandleCloseMany as u64,
+                raw.as_ptr() as u64,
+                handles.len() as u64,
+            );
             if (ret as i32) < 0 {
                 return Err(Error::from_raw(ret as i32));
             }
         }
+
+        // Already closed; don't let drop close them again
+        for handle in handles.iter_mut() {
+            core::mem::forget(core::mem::replace(handle, Self::INVALID));
+        }
         Ok(())
     }
 
@@ -198,9 +260,53 @@ impl Drop for Handle {
     }
 }
 
+/// Implement the methods every typed handle wrapper shares
+///
+/// `$rights` are the rights the kernel grants on a newly created object of
+/// the type; wrappers the kernel creates start with them.
+macro_rules! handle_wrapper {
+    ($name:ident, $rights:expr) => {
+        impl $name {
+            /// Rights on a newly created object of this type
+            pub const DEFAULT_RIGHTS: Rights = $rights;
+
+            /// Take the underlying handle
+            pub fn into_handle(self) -> Handle {
+                self.handle
+            }
+
+            /// Duplicate this handle with `rights` (see [`Handle::duplicate`])
+            pub fn duplicate(&self, rights: Rights) -> Result<Self> {
+                Ok(Self { handle: self.handle.duplicate(rights)? })
+            }
+
+            /// Replace this handle with one holding `rights` (see
+            /// [`Handle::replace`])
+            pub fn replace(self, rights: Rights) -> Result<Self> {
+                Ok(Self { handle: self.handle.replace(rights)? })
+            }
+        }
+    };
+}
+
+handle_wrapper!(Process, Rights::MANAGE);
+handle_wrapper!(Thread, Rights::MANAGE);
+handle_wrapper!(
+    Vmo,
+    Rights::READ.union(Rights::WRITE).union(Rights::EXECUTE).union(Rights::SIGNAL).union(Rights::MAP)
+);
+handle_wrapper!(Channel, Rights::READ.union(Rights::WRITE));
+handle_wrapper!(Event, Rights::SIGNAL.union(Rights::WAIT));
+handle_wrapper!(EventPair, Rights::SIGNAL.union(Rights::SIGNAL_PEER).union(Rights::WAIT));
+handle_wrapper!(
+    Counter,
+    Rights::READ.union(Rights::WRITE).union(Rights::WAIT).union(Rights::DUPLICATE).union(Rights::TRANSFER)
+);
+handle_wrapper!(Port, Rights::READ.union(Rights::WRITE));
+
 /// Wrapper for a Process handle
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Process {
     handle: Handle,
 }
@@ -223,11 +329,56 @@ impl Process {
             core::hint::unreachable_unchecked();
         }
     }
+
+    /// Read the process's memory at `vaddr` into `data`
+    ///
+    /// For debuggers; needs `kernel.enable-debugging-syscalls=true`, and
+    /// fails with `NotSupported` otherwise. Returns the number of bytes
+    /// read, fewer than asked for if the memory stops being mapped.
+    pub fn read_memory(&self, vaddr: u64, data: &mut [u8]) -> Result<usize> {
+        unsafe {
+            let ret = syscall4(
+                SyscallNumber::ProcessReadMemory as u64,
+                self.handle.raw() as u64,
+                vaddr,
+                data.as_mut_ptr() as u64,
+                data.len() as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(ret as usize)
+        }
+    }
+
+    /// Write `data` to the process's memory at `vaddr`
+    ///
+    /// As [`Process::read_memory`]. Read-only and executable memory is
+    /// written too, so a debugger can set breakpoints.
+    pub fn write_memory(&self, vaddr: u64, data: &[u8]) -> Result<usize> {
+        unsafe {
+            let ret = syscall4(
+                SyscallNumber::ProcessWriteMemory as u64,
+                self.handle.raw() as u64,
+                vaddr,
+                data.as_ptr() as u64,
+                data.len() as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(ret as usize)
+        }
+    }
 }
 
 /// Wrapper for a Thread handle
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Thread {
     handle: Handle,
 }
@@ -253,7 +404,7 @@ impl Thread {
             }
 
             Ok(Self {
-                handle: Handle::from_raw(ret as u32, Rights::all()),
+                handle: Handle::from_raw(ret as u32, Self::DEFAULT_RIGHTS),
             })
         }
     }
@@ -267,9 +418,56 @@ impl Thread {
     }
 }
 
+/// Write-back cached
+pub const CACHE_POLICY_DEFAULT: u32 = 0;
+
+/// Uncached, for device registers
+pub const CACHE_POLICY_UNCACHED: u32 = 1;
+
+/// Write-combining, for framebuffers
+pub const CACHE_POLICY_WRITE_COMBINING: u32 = 2;
+
+/// Write-through
+pub const CACHE_POLICY_WRITE_THROUGH: u32 = 3;
+
+/// `Vmo::op_range`: commit every page in the range
+pub const VMO_OP_COMMIT: u32 = 1;
+
+/// `Vmo::op_range`: free the committed pages in a page-aligned range
+pub const VMO_OP_DECOMMIT: u32 = 2;
+
+/// `Vmo::op_range`: invalidate the data cache over the range
+pub const VMO_OP_CACHE_INVALIDATE: u32 = 7;
+
+/// `Vmo::op_range`: write dirty cache lines back to memory
+pub const VMO_OP_CACHE_CLEAN: u32 = 8;
+
+/// `Vmo::op_range`: clean, then invalidate
+pub const VMO_OP_CACHE_CLEAN_INVALIDATE: u32 = 9;
+
+/// `Vmo::op_range`: zero the range
+pub const VMO_OP_ZERO: u32 = 10;
+
+/// `Vmo::op_range`: keep a discardable VMO's pages; returns 1 if they were
+/// discarded since the last unlock
+pub const VMO_OP_LOCK: u32 = 20;
+
+/// `Vmo::op_range`: lock only if the pages weren't discarded; fails with
+/// `NotFound` if they were
+pub const VMO_OP_TRY_LOCK: u32 = 21;
+
+/// `Vmo::op_range`: let the kernel discard the pages under memory pressure
+pub const VMO_OP_UNLOCK: u32 = 22;
+
+/// VMO creation option: the kernel may discard its pages while unlocked
+pub const VMO_CREATE_DISCARDABLE: u32 = 0x04;
+
+/// Signal asserted on a VMO when reclaim discards its pages
+pub const VMO_SIGNAL_DISCARDED: u64 = 0x10;
+
 /// Wrapper for a VMO (Virtual Memory Object) handle
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Vmo {
     handle: Handle,
 }
@@ -299,15 +497,16 @@ impl Vmo {
                 return Err(Error::from_raw(ret as i32));
             }
 
+            // Created resizable, so the handle can resize it
             Ok(Self {
-                handle: Handle::from_raw(ret as u32, Rights::all()),
+                handle: Handle::from_raw(ret as u32, Self::DEFAULT_RIGHTS | Rights::RESIZE),
             })
         }
     }
 
     /// Get the size of the VMO
     pub fn get_size(&self) -> Result<u64> {
-        if !self.handle.rights.contains(Rights::GET_PROPERTY) {
+        if !self.handle.rights.contains(Rights::READ) {
             return Err(Error::new(Status::AccessDenied));
         }
 
@@ -327,7 +526,7 @@ impl Vmo {
 
     /// Set the size of the VMO
     pub fn set_size(&self, size: u64) -> Result<()> {
-        if !self.handle.rights.contains(Rights::SET_PROPERTY) {
+        if !self.handle.rights.contains(Rights::WRITE | Rights::RESIZE) {
             return Err(Error::new(Status::AccessDenied));
         }
 
@@ -346,6 +545,78 @@ impl Vmo {
         }
     }
 
+    /// Set the cache policy of later mappings
+    ///
+    /// One of the `CACHE_POLICY_*` constants. Fails with `BadState` while
+    /// the VMO is mapped.
+    pub fn set_cache_policy(&self, policy: u32) -> Result<()> {
+        if !self.handle.rights.contains(Rights::MAP | Rights::WRITE) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        unsafe {
+            let ret = syscall2(
+                SyscallNumber::VmoSetCachePolicy as u64,
+                self.handle.raw() as u64,
+                policy as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Run one of the `VMO_OP_*` operations over `[offset, offset + size)`
+    pub fn op_range(&self, op: u32, offset: u64, size: u64) -> Result<()> {
+        unsafe {
+            let ret = syscall4(
+                SyscallNumber::VmoOpRange as u64,
+                self.handle.raw() as u64,
+                op as u64,
+                offset,
+                size,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Move `src`'s pages at `[src_offset, src_offset + len)` to
+    /// `[offset, offset + len)` of this VMO without copying
+    ///
+    /// Offsets and `len` must be page-aligned. The source range reads as
+    /// zero afterwards.
+    pub fn transfer_data(&self, offset: u64, len: u64, src: &Vmo, src_offset: u64) -> Result<()> {
+        if !self.handle.rights.contains(Rights::WRITE) || !src.handle.rights.contains(Rights::WRITE) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        unsafe {
+            let ret = syscall6(
+                SyscallNumber::VmoTransferData as u64,
+                self.handle.raw() as u64,
+                0, // options
+                offset,
+                len,
+                src.handle.raw() as u64,
+                src_offset,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(())
+        }
+    }
+
     /// Read from the VMO
     pub fn read(&self, offset: u64, data: &mut [u8]) -> Result<usize> {
         if !self.handle.rights.contains(Rights::READ) {
@@ -395,7 +666,7 @@ impl Vmo {
 
 /// Wrapper for a Channel handle
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Channel {
     handle: Handle,
 }
@@ -430,19 +701,128 @@ impl Channel {
 
             Ok((
                 Self {
-                    handle: Handle::from_raw(out0 as u32, Rights::all()),
+                    handle: Handle::from_raw(out0 as u32, Self::DEFAULT_RIGHTS),
                 },
                 Self {
-                    handle: Handle::from_raw(out1 as u32, Rights::all()),
+                    handle: Handle::from_raw(out1 as u32, Self::DEFAULT_RIGHTS),
                 },
             ))
         }
     }
 }
 
+/// One end of a socket pair, a byte stream to the other end
+///
+/// The kernel names sockets by ID rather than by handle, so a socket
+/// can't be duplicated or sent to another process. Dropping one shuts it
+/// down both ways, which the other end reads as `PeerClosed`.
+#[derive(Debug)]
+pub struct Socket {
+    id: u32,
+}
+
+impl Socket {
+    /// Stop reading
+    pub const SHUTDOWN_READ: u32 = 0x01;
+
+    /// Stop writing; the other end reads `PeerClosed` once it has read
+    /// everything written before
+    pub const SHUTDOWN_WRITE: u32 = 0x02;
+
+    /// Kernel status when nothing is buffered
+    const RX_ERR_SHOULD_WAIT: i32 = -18;
+
+    /// Kernel status when the other end can't read or write any more
+    const RX_ERR_PEER_CLOSED: i32 = -20;
+
+    /// Create a connected pair of sockets
+    pub fn create() -> Result<(Self, Self)> {
+        let mut out0: u64 = 0;
+        let mut out1: u64 = 0;
+        let ret = unsafe {
+            syscall3(
+                SyscallNumber::SocketCreate as u64,
+                0, // options
+                &mut out0 as *mut u64 as u64,
+                &mut out1 as *mut u64 as u64,
+            )
+        };
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+
+        Ok((Self { id: out0 as u32 }, Self { id: out1 as u32 }))
+    }
+
+    /// The kernel's ID for this end
+    pub fn raw(&self) -> u32 {
+        self.id
+    }
+
+    fn check(ret: u64) -> Result<()> {
+        match ret as i32 {
+            Self::RX_ERR_SHOULD_WAIT => Err(Error::new(Status::WouldBlock)),
+            Self::RX_ERR_PEER_CLOSED => Err(Error::new(Status::PeerClosed)),
+            ret if ret < 0 => Err(Error::from_raw(ret)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write `data` for the other end to read, returning how much was
+    /// written
+    pub fn write(&self, data: &[u8]) -> Result<usize> {
+        let mut actual: usize = 0;
+        let ret = unsafe {
+            syscall5(
+                SyscallNumber::SocketWrite as u64,
+                self.id as u64,
+                0, // options
+                data.as_ptr() as u64,
+                data.len() as u64,
+                &mut actual as *mut usize as u64,
+            )
+        };
+        Self::check(ret)?;
+        Ok(actual)
+    }
+
+    /// Read what the other end wrote into `buf`, returning how much was
+    /// read
+    ///
+    /// Never blocks: fails with `WouldBlock` when nothing is buffered and
+    /// `PeerClosed` once nothing more will be.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut actual: usize = 0;
+        let ret = unsafe {
+            syscall5(
+                SyscallNumber::SocketRead as u64,
+                self.id as u64,
+                0, // options
+                buf.as_mut_ptr() as u64,
+                buf.len() as u64,
+                &mut actual as *mut usize as u64,
+            )
+        };
+        Self::check(ret)?;
+        Ok(actual)
+    }
+
+    /// Stop reading, writing or both (`SHUTDOWN_*`)
+    pub fn shutdown(&self, options: u32) -> Result<()> {
+        let ret = unsafe { syscall2(SyscallNumber::SocketShutdown as u64, self.id as u64, options as u64) };
+        Self::check(ret)
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = self.shutdown(Self::SHUTDOWN_READ | Self::SHUTDOWN_WRITE);
+    }
+}
+
 /// Wrapper for an Event handle
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Event {
     handle: Handle,
 }
@@ -474,15 +854,219 @@ impl Event {
             }
 
             Ok(Self {
-                handle: Handle::from_raw(out as u32, Rights::all()),
+                handle: Handle::from_raw(out as u32, Self::DEFAULT_RIGHTS),
+            })
+        }
+    }
+}
+
+/// User signal 0
+pub const SIGNAL_USER_0: u64 = 0x0100_0000;
+
+/// User signal 1
+pub const SIGNAL_USER_1: u64 = 0x0200_0000;
+
+/// User signal 2
+pub const SIGNAL_USER_2: u64 = 0x0400_0000;
+
+/// User signal 3
+pub const SIGNAL_USER_3: u64 = 0x0800_0000;
+
+/// All user signals; the only bits an event pair accepts
+pub const SIGNAL_USER_ALL: u64 = 0xFF00_0000;
+
+/// Signal asserted on an event pair when the other side's last handle closes
+pub const EVENTPAIR_SIGNAL_PEER_CLOSED: u64 = 0x04;
+
+/// Wrapper for one side of an EventPair
+///
+/// Each side has its own user signal bits. `signal` changes this side's
+/// bits and `signal_peer` the other side's, which fails with `PeerClosed`
+/// once the other side has no handles left.
+#[repr(C)]
+#[derive(Debug)]
+pub struct EventPair {
+    handle: Handle,
+}
+
+impl EventPair {
+    /// Create a new event pair handle from a raw handle
+    pub unsafe fn from_handle(handle: Handle) -> Self {
+        Self { handle }
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Create a new event pair, returning both sides
+    pub fn create() -> Result<(Self, Self)> {
+        unsafe {
+            let ret = syscall1(
+                SyscallNumber::EventpairCreate as u64,
+                0, // options
+            );
+
+            if (ret as i64) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            // Lower 32 bits are the first side, upper 32 bits the second
+            Ok((
+                Self { handle: Handle::from_raw(ret as u32, Self::DEFAULT_RIGHTS) },
+                Self { handle: Handle::from_raw((ret >> 32) as u32, Self::DEFAULT_RIGHTS) },
+            ))
+        }
+    }
+
+    /// Clear then set user signals on this side
+    pub fn signal(&self, clear_mask: u64, set_mask: u64) -> Result<()> {
+        self.signal_with(SyscallNumber::ObjectSignal, Rights::SIGNAL, clear_mask, set_mask)
+    }
+
+    /// Clear then set user signals on the other side
+    pub fn signal_peer(&self, clear_mask: u64, set_mask: u64) -> Result<()> {
+        self.signal_with(SyscallNumber::ObjectSignalPeer, Rights::SIGNAL_PEER, clear_mask, set_mask)
+    }
+
+    fn signal_with(&self, nr: SyscallNumber, right: Rights, clear_mask: u64, set_mask: u64) -> Result<()> {
+        if !self.handle.rights().contains(right) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+        if (clear_mask | set_mask) & !SIGNAL_USER_ALL != 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        unsafe {
+            let ret = syscall3(nr as u64, self.handle.raw() as u64, clear_mask, set_mask);
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Signal asserted on a counter while its value is above zero
+pub const COUNTER_SIGNAL_POSITIVE: u64 = 0x10;
+
+/// Signal asserted on a counter while its value is zero or below
+pub const COUNTER_SIGNAL_NON_POSITIVE: u64 = 0x20;
+
+/// Wrapper for a Counter handle
+///
+/// A signed 64-bit value shared between processes. A producer `add`s one
+/// per item; a consumer waits for `COUNTER_SIGNAL_POSITIVE` and `add`s
+/// back the negative of what it took.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Counter {
+    handle: Handle,
+}
+
+impl Counter {
+    /// Create a new counter handle from a raw handle
+    pub unsafe fn from_handle(handle: Handle) -> Self {
+        Self { handle }
+    }
+
+    /// Get the underlying handle
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Create a new counter with value 0
+    pub fn create() -> Result<Self> {
+        unsafe {
+            let ret = syscall1(
+                SyscallNumber::CounterCreate as u64,
+                0, // options
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(Self {
+                handle: Handle::from_raw(ret as u32, Self::DEFAULT_RIGHTS),
             })
         }
     }
+
+    /// Add `amount` to the value
+    ///
+    /// Fails without changing the value if the result would overflow.
+    pub fn add(&self, amount: i64) -> Result<()> {
+        if !self.handle.rights().contains(Rights::WRITE) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        unsafe {
+            let ret = syscall2(
+                SyscallNumber::CounterAdd as u64,
+                self.handle.raw() as u64,
+                amount as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Read the value
+    pub fn read(&self) -> Result<i64> {
+        if !self.handle.rights().contains(Rights::READ) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        unsafe {
+            let mut value: i64 = 0;
+
+            let ret = syscall2(
+                SyscallNumber::CounterRead as u64,
+                self.handle.raw() as u64,
+                &mut value as *mut i64 as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(value)
+        }
+    }
+
+    /// Overwrite the value
+    pub fn write(&self, value: i64) -> Result<()> {
+        if !self.handle.rights().contains(Rights::WRITE) {
+            return Err(Error::new(Status::AccessDenied));
+        }
+
+        unsafe {
+            let ret = syscall2(
+                SyscallNumber::CounterWrite as u64,
+                self.handle.raw() as u64,
+                value as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(())
+        }
+    }
 }
 
 /// Wrapper for a Port handle
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Port {
     handle: Handle,
 }
@@ -513,8 +1097,87 @@ impl Port {
             }
 
             Ok(Self {
-                handle: Handle::from_raw(ret as u32, Rights::all()),
+                handle: Handle::from_raw(ret as u32, Self::DEFAULT_RIGHTS),
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::ManuallyDrop;
+
+    /// A handle to nothing, holding `rights`
+    ///
+    /// None of these tests reach the kernel, and the handle is never
+    /// dropped, so it is never closed.
+    fn handle(rights: Rights) -> ManuallyDrop<Handle> {
+        ManuallyDrop::new(unsafe { Handle::from_raw(1, rights) })
+    }
+
+    fn vmo(rights: Rights) -> ManuallyDrop<Vmo> {
+        ManuallyDrop::new(unsafe { Vmo::from_handle(ManuallyDrop::into_inner(handle(rights))) })
+    }
+
+    #[test]
+    fn test_reduced_rights() {
+        let h = handle(Rights::READ | Rights::WRITE | Rights::DUPLICATE);
+        assert_eq!(h.reduced_rights(Rights::READ).ok(), Some(Rights::READ));
+        assert_eq!(h.reduced_rights(Rights::empty()).ok(), Some(Rights::empty()));
+        assert_eq!(h.reduced_rights(Rights::SAME_RIGHTS).ok(), Some(h.rights()));
+        // SAME_RIGHTS wins over whatever else is asked for
+        assert_eq!(h.reduced_rights(Rights::SAME_RIGHTS | Rights::MAP).ok(), Some(h.rights()));
+        assert_eq!(h.reduced_rights(Rights::READ | Rights::MAP).unwrap_err().status(), Status::InvalidArgs);
+    }
+
+    #[test]
+    fn test_require() {
+        let h = handle(Rights::READ | Rights::MAP);
+        assert!(h.require(Rights::READ).is_ok());
+        assert!(h.require(Rights::READ | Rights::MAP).is_ok());
+        assert_eq!(h.require(Rights::READ | Rights::WRITE).unwrap_err().status(), Status::AccessDenied);
+    }
+
+    #[test]
+    fn test_duplicate_checks_rights() {
+        let h = handle(Rights::READ | Rights::WRITE);
+        assert_eq!(h.duplicate(Rights::SAME_RIGHTS).unwrap_err().status(), Status::AccessDenied);
+
+        // With DUPLICATE, asking for more than is held is the caller's mistake
+        let h = handle(Rights::READ | Rights::DUPLICATE);
+        assert_eq!(h.duplicate(Rights::WRITE).unwrap_err().status(), Status::InvalidArgs);
+    }
+
+    #[test]
+    fn test_vmo_checks_rights() {
+        let vmo = vmo(Rights::READ);
+        let mut buf = [0u8; 4];
+        assert_eq!(vmo.write(0, &buf).unwrap_err().status(), Status::AccessDenied);
+        assert_eq!(vmo.set_size(4096).unwrap_err().status(), Status::AccessDenied);
+        assert_eq!(vmo.set_cache_policy(0).unwrap_err().status(), Status::AccessDenied);
+
+        let vmo = self::vmo(Rights::WRITE);
+        assert_eq!(vmo.read(0, &mut buf).unwrap_err().status(), Status::AccessDenied);
+        assert_eq!(vmo.get_size().unwrap_err().status(), Status::AccessDenied);
+
+        // Both sides of a transfer must be writable
+        let src = self::vmo(Rights::READ);
+        assert_eq!(vmo.transfer_data(0, 4096, &src, 0).unwrap_err().status(), Status::AccessDenied);
+    }
+
+    #[test]
+    fn test_event_pair_checks_rights_and_signals() {
+        let rights = Rights::SIGNAL;
+        let pair = ManuallyDrop::new(unsafe { EventPair::from_handle(ManuallyDrop::into_inner(handle(rights))) });
+        assert_eq!(pair.signal_peer(0, 1 << 24).unwrap_err().status(), Status::AccessDenied);
+        // Only user signals may be touched
+        assert_eq!(pair.signal(0, 1).unwrap_err().status(), Status::InvalidArgs);
+    }
+
+    #[test]
+    fn test_close_many_rejects_too_many() {
+        let mut handles: [Handle; HANDLE_CLOSE_MANY_MAX + 1] = core::array::from_fn(|_| Handle::INVALID);
+        assert_eq!(Handle::close_many(&mut handles).unwrap_err().status(), Status::InvalidArgs);
+    }
+}
@@ -11,6 +11,10 @@
 //! - Handle types for kernel objects
 //! - Error handling
 //! - Object type definitions
+//! - VMO mappings that unmap on drop
+//! - The handles a process is started with
+//! - One-time initialization (Once, OnceCell, Lazy)
+//! - Kernel version and feature queries
 //!
 //! # Examples
 //!
@@ -37,16 +41,24 @@
 
 #![no_std]
 
+#[cfg(test)]
+extern crate std;
+
 // Core modules
 pub mod error;
 pub mod syscall;
 pub mod handles;
 pub mod object;
+pub mod mapping;
+pub mod startup;
+pub mod once;
+mod futex;
 
 // Re-export commonly used types
 pub use error::{Error, Result, Status};
 pub use syscall::SyscallNumber;
-pub use handles::{Handle, Rights, Process, Thread, Vmo, Channel, Event, Port};
+pub use handles::{Handle, Rights, Process, Thread, Vmo, Channel, Socket, Event, EventPair, Counter, Port};
+pub use mapping::{MapPerms, Mapping};
 pub use object::{ObjectType, ObjectInfo, HandleInfo, VmoInfo, ProcessInfo, ThreadInfo};
 
 // C-compatible FFI exports
@@ -94,7 +106,7 @@ pub mod process {
             }
 
             Ok(Process {
-                handle: Handle::from_raw(ret as u32, Rights::all()),
+                handle: Handle::from_raw(ret as u32, Process::DEFAULT_RIGHTS),
             })
         }
     }
@@ -146,7 +158,7 @@ pub mod thread {
             }
 
             Ok(Thread {
-                handle: Handle::from_raw(ret as u32, Rights::all()),
+                handle: Handle::from_raw(ret as u32, Thread::DEFAULT_RIGHTS),
             })
         }
     }
@@ -185,6 +197,27 @@ pub mod vmar {
     use super::*;
     use crate::syscall::SyscallNumber;
 
+    /// Map at exactly the given address; fail if it is in use
+    pub const SPECIFIC: u32 = 0x100;
+    /// Map at exactly the given address, replacing mappings there
+    pub const SPECIFIC_OVERWRITE: u32 = 0x200;
+    /// Map uncached (physical VMOs only)
+    pub const MAP_UNCACHED: u32 = 0x4000;
+    /// Map write-combining (physical VMOs only)
+    pub const MAP_WRITE_COMBINING: u32 = 0x8000;
+    /// Map anywhere that ends at or below the given address
+    pub const OFFSET_IS_UPPER_LIMIT: u32 = 0x1_0000;
+    /// Use the lowest free range instead of a randomized one
+    pub const COMPACT: u32 = 0x2_0000;
+
+    /// Placement alignments, as log2 in bits 24-28
+    pub const ALIGN_SHIFT: u32 = 24;
+    pub const ALIGN_4KB: u32 = 12 << ALIGN_SHIFT;
+    pub const ALIGN_64KB: u32 = 16 << ALIGN_SHIFT;
+    pub const ALIGN_2MB: u32 = 21 << ALIGN_SHIFT;
+    pub const ALIGN_1GB: u32 = 30 << ALIGN_SHIFT;
+    pub const ALIGN_4GB: u32 = 32 << ALIGN_SHIFT;
+
     /// Get the root VMAR for the current process
     pub fn root_self() -> Result<Handle> {
         unsafe {
@@ -199,7 +232,7 @@ pub mod vmar {
                 return Err(Error::from_raw(ret as i32));
             }
 
-            Ok(Handle::from_raw(out as u32, Rights::all()))
+            Ok(Handle::from_raw(out as u32, Rights::READ | Rights::WRITE | Rights::MAP))
         }
     }
 
@@ -317,7 +350,1915 @@ pub mod bootstrap {
                 return Err(Error::from_raw(ret as i32));
             }
 
-            Ok(Handle::from_raw(out as u32, Rights::all()))
+            Ok(Handle::from_raw(out as u32, Rights::MANAGE))
+        }
+    }
+}
+
+/// Kernel CPRNG
+pub mod cprng {
+    use super::*;
+    use crate::syscall::{syscall2, SyscallNumber};
+
+    /// Largest request the kernel accepts in one call
+    pub const MAX_LEN: usize = 256;
+
+    /// Fill `buf` with cryptographically secure random bytes
+    pub fn draw(buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(MAX_LEN) {
+            // Cannot fail for a valid, writable buffer of at most MAX_LEN
+            unsafe {
+                syscall2(
+                    SyscallNumber::CprngDraw as u64,
+                    chunk.as_mut_ptr() as u64,
+                    chunk.len() as u64,
+                );
+            }
+        }
+    }
+
+    /// Mix `data` into the kernel CPRNG
+    ///
+    /// The kernel does not credit the data as entropy.
+    pub fn add_entropy(data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(MAX_LEN) {
+            unsafe {
+                let ret = syscall2(
+                    SyscallNumber::CprngAddEntropy as u64,
+                    chunk.as_ptr() as u64,
+                    chunk.len() as u64,
+                );
+
+                if (ret as i32) < 0 {
+                    return Err(Error::from_raw(ret as i32));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kernel version and features
+///
+/// Lets a binary find out what the running kernel supports and adapt,
+/// rather than assuming it matches the ABI libsys was built against.
+pub mod system {
+    use super::*;
+    use crate::syscall::{syscall0, syscall1, syscall3, SyscallNumber};
+
+    /// ABI version libsys was built against
+    pub const ABI_VERSION: u32 = SyscallNumber::ABI_VERSION;
+
+    /// Feature kind for [`supported_syscalls`]
+    const FEATURE_KIND_SYSCALLS: u32 = 0;
+
+    /// Kernel version information
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Version {
+        /// Syscall ABI version of the running kernel
+        pub abi_version: u32,
+        reserved: u32,
+        kernel_version: [u8; 32],
+        build_id: [u8; 64],
+    }
+
+    impl Version {
+        /// Kernel version
+        pub fn kernel_version(&self) -> &str {
+            nul_padded(&self.kernel_version)
+        }
+
+        /// Kernel build ID
+        pub fn build_id(&self) -> &str {
+            nul_padded(&self.build_id)
+        }
+    }
+
+    /// The string in a NUL-padded buffer
+    fn nul_padded(buf: &[u8]) -> &str {
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        core::str::from_utf8(&buf[..len]).unwrap_or("")
+    }
+
+    /// Get the running kernel's version
+    ///
+    /// Fails with `NotSupported` on kernels older than ABI version 2.
+    pub fn get_version() -> Result<Version> {
+        let mut version = Version {
+            abi_version: 0,
+            reserved: 0,
+            kernel_version: [0; 32],
+            build_id: [0; 64],
+        };
+        unsafe {
+            let ret = syscall1(
+                SyscallNumber::SystemGetVersion as u64,
+                &mut version as *mut Version as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(version)
+    }
+
+    /// Get the bitmap of syscalls the kernel implements
+    ///
+    /// Bit `n % 64` of word `n / 64` is set if syscall `n` is implemented.
+    pub fn supported_syscalls() -> Result<[u64; 4]> {
+        let mut words = [0u64; 4];
+        unsafe {
+            let ret = syscall3(
+                SyscallNumber::SystemGetFeatures as u64,
+                FEATURE_KIND_SYSCALLS as u64,
+                words.as_mut_ptr() as u64,
+                core::mem::size_of_val(&words) as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(words)
+    }
+
+    /// Whether the kernel implements `syscall`
+    ///
+    /// False on kernels too old to say.
+    pub fn has_syscall(syscall: SyscallNumber) -> bool {
+        let n = syscall as usize;
+        match supported_syscalls() {
+            Ok(words) => n < 256 && words[n / 64] & (1 << (n % 64)) != 0,
+            Err(_) => false,
+        }
+    }
+
+    /// Total physical memory in bytes
+    ///
+    /// Fails with `NotSupported` on kernels older than ABI version 2.
+    pub fn phys_mem() -> Result<u64> {
+        let ret = unsafe { syscall0(SyscallNumber::SystemGetPhysMem as u64) };
+        if (ret as i64) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(ret)
+    }
+
+    /// Number of online CPUs
+    ///
+    /// Fails with `NotSupported` on kernels older than ABI version 2.
+    pub fn num_cpus() -> Result<u32> {
+        let ret = unsafe { syscall0(SyscallNumber::SystemGetNumCpus as u64) };
+        if (ret as i64) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(ret as u32)
+    }
+}
+
+/// Crash log left by the previous boot
+pub mod crashlog {
+    use super::*;
+    use crate::syscall::{syscall5, SyscallNumber};
+
+    /// Read up to `buf.len()` bytes of the log starting at `offset`
+    ///
+    /// Returns the number of bytes read, 0 at the end of the log. Fails if
+    /// the previous boot left no crash log. `resource` must be the root
+    /// resource.
+    pub fn read(resource: &Handle, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = syscall5(
+                SyscallNumber::SystemCrashlogRead as u64,
+                resource.raw() as u64,
+                offset as u64,
+                buf.as_mut_ptr() as u64,
+                buf.len() as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(actual)
+    }
+}
+
+/// System power control
+pub mod power {
+    use super::*;
+    use crate::syscall::{syscall3, SyscallNumber};
+
+    /// Reboot
+    pub const REBOOT: u32 = 5;
+
+    /// Reboot into the bootloader, where the platform has one
+    pub const REBOOT_BOOTLOADER: u32 = 6;
+
+    /// Reboot into recovery, where the platform has one
+    pub const REBOOT_RECOVERY: u32 = 7;
+
+    /// Power off
+    pub const SHUTDOWN: u32 = 8;
+
+    /// Issue power control command `cmd`
+    ///
+    /// Only returns on failure for the reboot and shutdown commands.
+    /// `resource` must be the root resource.
+    pub fn powerctl(resource: &Handle, cmd: u32) -> Result<()> {
+        unsafe {
+            let ret = syscall3(
+                SyscallNumber::SystemPowerctl as u64,
+                resource.raw() as u64,
+                cmd as u64,
+                0,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reboot the machine
+    pub fn reboot(resource: &Handle) -> Result<()> {
+        powerctl(resource, REBOOT)
+    }
+
+    /// Power the machine off
+    pub fn poweroff(resource: &Handle) -> Result<()> {
+        powerctl(resource, SHUTDOWN)
+    }
+}
+
+/// System-wide events
+pub mod system_event {
+    use super::*;
+    use crate::syscall::{syscall2, SyscallNumber};
+
+    /// Signaled when the system is out of memory
+    pub const OUT_OF_MEMORY: u32 = 1;
+
+    /// Signaled at critical memory pressure
+    pub const MEMORY_PRESSURE_CRITICAL: u32 = 2;
+
+    /// Signaled at warning memory pressure
+    pub const MEMORY_PRESSURE_WARNING: u32 = 3;
+
+    /// Signaled when memory pressure is back to normal
+    pub const MEMORY_PRESSURE_NORMAL: u32 = 4;
+
+    /// Get the event for `kind`, asserting `USER_0` while it applies
+    ///
+    /// `resource` must be the root resource.
+    pub fn get(resource: &Handle, kind: u32) -> Result<Handle> {
+        unsafe {
+            let ret = syscall2(
+                SyscallNumber::SystemGetEvent as u64,
+                resource.raw() as u64,
+                kind as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(Handle::from_raw(ret as u32, Event::DEFAULT_RIGHTS))
+        }
+    }
+}
+
+/// Resources granting access to hardware
+pub mod resource {
+    use super::*;
+    use crate::syscall::{syscall6, SyscallNumber};
+
+    /// Interrupt vectors
+    pub const KIND_IRQ: u32 = 1;
+
+    /// Physical address range
+    pub const KIND_MMIO: u32 = 2;
+
+    /// System operations
+    pub const KIND_SYSTEM: u32 = 3;
+
+    /// Hypervisor operations
+    pub const KIND_HYPERVISOR: u32 = 4;
+
+    /// x86 I/O ports
+    pub const KIND_IOPORT: u32 = 5;
+
+    /// EFI runtime services
+    pub const KIND_EFI: u32 = 6;
+
+    /// Derive a resource of `kind` covering `[base, base + size)`
+    ///
+    /// `parent` must be the root resource or a resource of the same kind
+    /// covering the range.
+    pub fn create(parent: &Handle, kind: u32, base: u64, size: usize, name: &str) -> Result<Handle> {
+        unsafe {
+            let ret = syscall6(
+                SyscallNumber::ResourceCreate as u64,
+                parent.raw() as u64,
+                kind as u64,
+                base,
+                size as u64,
+                name.as_ptr() as u64,
+                name.len() as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+
+            Ok(Handle::from_raw(ret as u32, Rights::DUPLICATE | Rights::TRANSFER))
+        }
+    }
+}
+
+/// Kernel counters
+pub mod kcounter {
+    use super::*;
+    use crate::syscall::{syscall5, SyscallNumber};
+
+    /// Longest counter name, including the terminating NUL
+    pub const MAX_NAME: usize = 48;
+
+    /// Counter sums its per-CPU values
+    pub const KIND_SUM: u32 = 0;
+
+    /// Counter takes the maximum of its per-CPU values
+    pub const KIND_MAX: u32 = 1;
+
+    /// One counter in a snapshot
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Record {
+        /// NUL-padded name
+        pub name: [u8; MAX_NAME],
+
+        /// `KIND_SUM` or `KIND_MAX`
+        pub kind: u32,
+
+        /// Reserved, zero
+        pub reserved: u32,
+
+        /// Value combined across CPUs
+        pub value: i64,
+    }
+
+    impl Record {
+        /// An empty record, for sizing snapshot buffers
+        pub const EMPTY: Self = Self { name: [0; MAX_NAME], kind: 0, reserved: 0, value: 0 };
+
+        /// Counter name
+        pub fn name(&self) -> &str {
+            let len = self.name.iter().position(|&b| b == 0).unwrap_or(MAX_NAME);
+            core::str::from_utf8(&self.name[..len]).unwrap_or("")
+        }
+    }
+
+    /// Snapshot the kernel counters into `records`, sorted by name
+    ///
+    /// Returns (records written, counters available); if the second is
+    /// larger, `records` was too small. `resource` must be the root
+    /// resource.
+    pub fn read(resource: &Handle, records: &mut [Record]) -> Result<(usize, usize)> {
+        let mut actual: usize = 0;
+        let mut avail: usize = 0;
+        unsafe {
+            let ret = syscall5(
+                SyscallNumber::KcounterRead as u64,
+                resource.raw() as u64,
+                records.as_mut_ptr() as u64,
+                core::mem::size_of_val(records) as u64,
+                &mut actual as *mut usize as u64,
+                &mut avail as *mut usize as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok((actual, avail))
+    }
+}
+
+/// Kernel trace
+pub mod ktrace {
+    use super::*;
+    use crate::syscall::{syscall4, syscall5, SyscallNumber};
+
+    /// Trace groups
+    pub const GRP_META: u32 = 0x0001;
+    pub const GRP_LIFECYCLE: u32 = 0x0002;
+    pub const GRP_SCHEDULER: u32 = 0x0004;
+    pub const GRP_SYSCALL: u32 = 0x0008;
+    pub const GRP_IRQ: u32 = 0x0010;
+    pub const GRP_IPC: u32 = 0x0020;
+    pub const GRP_PROBE: u32 = 0x0040;
+    pub const GRP_VM: u32 = 0x0080;
+    pub const GRP_ALL: u32 = 0x00ff;
+
+    /// Event tags, `(group << 16) | event`
+    pub const TAG_PROBE_NAME: u32 = (GRP_META << 16) | 1;
+    pub const TAG_CONTEXT_SWITCH: u32 = (GRP_SCHEDULER << 16) | 1;
+    pub const TAG_SYSCALL_ENTER: u32 = (GRP_SYSCALL << 16) | 1;
+    pub const TAG_SYSCALL_EXIT: u32 = (GRP_SYSCALL << 16) | 2;
+    pub const TAG_IRQ_ENTER: u32 = (GRP_IRQ << 16) | 1;
+    pub const TAG_IRQ_EXIT: u32 = (GRP_IRQ << 16) | 2;
+    pub const TAG_PAGE_FAULT: u32 = (GRP_VM << 16) | 1;
+    pub const TAG_VMAR_MAP: u32 = (GRP_VM << 16) | 2;
+    pub const TAG_VMAR_UNMAP: u32 = (GRP_VM << 16) | 3;
+    pub const TAG_PROBE: u32 = GRP_PROBE << 16;
+
+    const ACTION_NEW_PROBE: u64 = 0;
+    const ACTION_START: u64 = 1;
+    const ACTION_STOP: u64 = 2;
+    const ACTION_REWIND: u64 = 3;
+
+    /// One trace record
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Record {
+        /// Monotonic time in nanoseconds
+        pub ts: u64,
+
+        /// Group and event
+        pub tag: u32,
+
+        /// CPU that wrote the record
+        pub cpu: u16,
+
+        /// Event-specific id: syscall number, vector or probe
+        pub id: u16,
+
+        /// Event-specific arguments
+        pub args: [u64; 2],
+    }
+
+    impl Record {
+        /// An empty record, for sizing read buffers
+        pub const EMPTY: Self = Self { ts: 0, tag: 0, cpu: 0, id: 0, args: [0; 2] };
+
+        /// Group of the record's event
+        pub fn group(&self) -> u32 {
+            self.tag >> 16
+        }
+    }
+
+    /// Read trace records starting at record `index` into `records`
+    ///
+    /// Returns the number of records read, 0 at the end of the trace.
+    /// Stop tracing first for a consistent snapshot. `resource` must be the
+    /// root resource.
+    pub fn read(resource: &Handle, index: usize, records: &mut [Record]) -> Result<usize> {
+        let size = core::mem::size_of::<Record>();
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = syscall5(
+                SyscallNumber::KtraceRead as u64,
+                resource.raw() as u64,
+                records.as_mut_ptr() as u64,
+                (index * size) as u64,
+                core::mem::size_of_val(records) as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(actual / size)
+    }
+
+    fn control(resource: &Handle, action: u64, options: u32, ptr: u64) -> Result<u64> {
+        let ret = unsafe {
+            syscall4(
+                SyscallNumber::KtraceControl as u64,
+                resource.raw() as u64,
+                action,
+                options as u64,
+                ptr,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(ret)
+    }
+
+    /// Start tracing the groups in `groups`
+    pub fn start(resource: &Handle, groups: u32) -> Result<()> {
+        control(resource, ACTION_START, groups, 0).map(|_| ())
+    }
+
+    /// Stop tracing
+    pub fn stop(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_STOP, 0, 0).map(|_| ())
+    }
+
+    /// Discard the trace
+    pub fn rewind(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_REWIND, 0, 0).map(|_| ())
+    }
+
+    /// Register a probe and return its id for `write`
+    ///
+    /// The trace keeps the first 16 bytes of the name.
+    pub fn add_probe(resource: &Handle, name: &str) -> Result<u16> {
+        let mut buf = [0u8; 32];
+        let len = name.len().min(buf.len() - 1);
+        buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+        control(resource, ACTION_NEW_PROBE, 0, buf.as_ptr() as u64).map(|id| id as u16)
+    }
+
+    /// Record probe `probe` with two arguments
+    pub fn write(resource: &Handle, probe: u16, arg0: u32, arg1: u32) -> Result<()> {
+        let ret = unsafe {
+            syscall4(
+                SyscallNumber::KtraceWrite as u64,
+                resource.raw() as u64,
+                probe as u64,
+                arg0 as u64,
+                arg1 as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(())
+    }
+}
+
+/// Sampling profiler
+pub mod profiler {
+    use super::*;
+    use crate::syscall::{syscall3, syscall5, SyscallNumber};
+
+    /// Frames kept per sample, the sampled pc included
+    pub const MAX_FRAMES: usize = 8;
+
+    /// The sample interrupted user code
+    pub const FLAG_USER: u16 = 1 << 0;
+
+    const ACTION_START: u64 = 1;
+    const ACTION_STOP: u64 = 2;
+    const ACTION_REWIND: u64 = 3;
+
+    /// One sample
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Sample {
+        /// Monotonic time in nanoseconds
+        pub ts: u64,
+
+        /// Process of the interrupted thread, 0 if unknown
+        pub pid: u64,
+
+        /// Interrupted thread
+        pub tid: u64,
+
+        /// CPU that took the sample
+        pub cpu: u16,
+
+        /// `FLAG_*` bits
+        pub flags: u16,
+
+        /// Entries of `pcs` in use
+        pub depth: u16,
+
+        /// Reserved
+        pub reserved: u16,
+
+        /// Sampled pc, then return addresses, innermost first
+        pub pcs: [u64; MAX_FRAMES],
+    }
+
+    impl Sample {
+        /// An empty sample, for sizing read buffers
+        pub const EMPTY: Self = Self {
+            ts: 0,
+            pid: 0,
+            tid: 0,
+            cpu: 0,
+            flags: 0,
+            depth: 0,
+            reserved: 0,
+            pcs: [0; MAX_FRAMES],
+        };
+
+        /// Whether the sample interrupted user code
+        pub fn is_user(&self) -> bool {
+            self.flags & FLAG_USER != 0
+        }
+
+        /// Sampled pc and return addresses, innermost first
+        pub fn frames(&self) -> &[u64] {
+            &self.pcs[..(self.depth as usize).min(MAX_FRAMES)]
+        }
+    }
+
+    /// Read samples starting at sample `index` into `samples`
+    ///
+    /// Returns the number of samples read, 0 at the end. Stop the profiler
+    /// first for a consistent snapshot. `resource` must be the root
+    /// resource.
+    pub fn read(resource: &Handle, index: usize, samples: &mut [Sample]) -> Result<usize> {
+        let size = core::mem::size_of::<Sample>();
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = syscall5(
+                SyscallNumber::ProfilerRead as u64,
+                resource.raw() as u64,
+                samples.as_mut_ptr() as u64,
+                (index * size) as u64,
+                core::mem::size_of_val(samples) as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(actual / size)
+    }
+
+    fn control(resource: &Handle, action: u64, options: u32) -> Result<()> {
+        let ret = unsafe {
+            syscall3(
+                SyscallNumber::ProfilerControl as u64,
+                resource.raw() as u64,
+                action,
+                options as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
         }
+        Ok(())
+    }
+
+    /// Start sampling every `period_us` microseconds, 0 for the kernel's
+    /// default
+    pub fn start(resource: &Handle, period_us: u32) -> Result<()> {
+        control(resource, ACTION_START, period_us)
+    }
+
+    /// Stop sampling
+    pub fn stop(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_STOP, 0)
+    }
+
+    /// Discard the samples
+    pub fn rewind(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_REWIND, 0)
+    }
+}
+
+/// Performance counters
+///
+/// Counts hardware events on every CPU or for one thread. A counting
+/// session is started with a [`Config`], stopped, and its totals read
+/// with [`read`]. With a sample period, counter 0 also records profiler
+/// samples, read back with [`profiler::read`](super::profiler::read).
+pub mod perfmon {
+    use super::*;
+    use crate::syscall::{syscall4, SyscallNumber};
+
+    /// Most counters in a configuration
+    pub const MAX_COUNTERS: usize = 4;
+
+    /// Generic events
+    pub const EVENT_CYCLES: u32 = 1;
+    pub const EVENT_INSTRUCTIONS: u32 = 2;
+    pub const EVENT_CACHE_MISSES: u32 = 3;
+    pub const EVENT_BRANCH_MISSES: u32 = 4;
+
+    /// A raw hardware event in the low 16 bits
+    pub const EVENT_RAW: u32 = 1 << 31;
+
+    /// Count every CPU
+    pub const MODE_CPU: u32 = 0;
+
+    /// Count one thread
+    pub const MODE_THREAD: u32 = 1;
+
+    /// Count user code
+    pub const FLAG_USER: u32 = 1 << 0;
+
+    /// Count kernel code
+    pub const FLAG_KERNEL: u32 = 1 << 1;
+
+    /// `cpu` of the totals read in thread mode
+    pub const CPU_THREAD: u32 = u32::MAX;
+
+    const ACTION_GET_PROPERTIES: u64 = 0;
+    const ACTION_START: u64 = 1;
+    const ACTION_STOP: u64 = 2;
+
+    /// What the PMU can do
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Properties {
+        /// Counters usable at once, 0 without a PMU
+        pub counters: u32,
+
+        /// Width of the counters in bits
+        pub counter_bits: u32,
+
+        /// Bit `n` is set if generic event `n` can be counted
+        pub events: u32,
+
+        /// Reserved
+        pub reserved: u32,
+    }
+
+    /// A counting session
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Config {
+        /// Event of each counter, `EVENT_*`
+        pub events: [u32; MAX_COUNTERS],
+
+        /// Counters in use
+        pub count: u32,
+
+        /// `MODE_*`
+        pub mode: u32,
+
+        /// `FLAG_*` bits, 0 for both
+        pub flags: u32,
+
+        /// Reserved, 0
+        pub reserved: u32,
+
+        /// Thread to count in thread mode
+        pub thread: u64,
+
+        /// Events of counter 0 between profiler samples, 0 for none
+        pub sample_period: u64,
+    }
+
+    /// Totals of one CPU, or of the counted thread
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Counts {
+        /// CPU, or `CPU_THREAD`
+        pub cpu: u32,
+
+        /// Entries of `values` in use
+        pub count: u32,
+
+        /// Events counted by each counter
+        pub values: [u64; MAX_COUNTERS],
+    }
+
+    impl Counts {
+        /// Events counted by the counters in use
+        pub fn values(&self) -> &[u64] {
+            &self.values[..(self.count as usize).min(MAX_COUNTERS)]
+        }
+    }
+
+    fn control(resource: &Handle, action: u64, buffer: u64, size: usize) -> Result<()> {
+        let ret = unsafe {
+            syscall4(
+                SyscallNumber::PerfmonControl as u64,
+                resource.raw() as u64,
+                action,
+                buffer,
+                size as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(())
+    }
+
+    /// What the PMU can do. `resource` must be the root resource.
+    pub fn properties(resource: &Handle) -> Result<Properties> {
+        let mut props = Properties::default();
+        control(
+            resource,
+            ACTION_GET_PROPERTIES,
+            &mut props as *mut Properties as u64,
+            core::mem::size_of::<Properties>(),
+        )?;
+        Ok(props)
+    }
+
+    /// Start counting
+    ///
+    /// Fails with `BadState` if a session is already running and
+    /// `NotSupported` if an event can't be counted. A thread is counted
+    /// from the next time it is scheduled.
+    pub fn start(resource: &Handle, config: &Config) -> Result<()> {
+        control(
+            resource,
+            ACTION_START,
+            config as *const Config as u64,
+            core::mem::size_of::<Config>(),
+        )
+    }
+
+    /// Stop counting
+    pub fn stop(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_STOP, 0, 0)
+    }
+
+    /// Read the totals of the running or last session into `counts`
+    ///
+    /// Returns the number of entries read: one per CPU, or one for the
+    /// thread. Totals are exact once the session is stopped.
+    pub fn read(resource: &Handle, counts: &mut [Counts]) -> Result<usize> {
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = syscall4(
+                SyscallNumber::PerfmonRead as u64,
+                resource.raw() as u64,
+                counts.as_mut_ptr() as u64,
+                core::mem::size_of_val(counts) as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok(actual)
+    }
+}
+
+/// Hardware instruction trace
+///
+/// Records the branches taken on every CPU or by one thread into trace
+/// buffers, Intel PT packets on x86 and ETE packets on arm64. A session's
+/// buffers are allocated with a [`Config`], tracing is started and
+/// stopped, and each stopped buffer is handed over as a VMO with
+/// [`buffer`] for a decoder to read.
+pub mod itrace {
+    use super::*;
+    use crate::syscall::{syscall4, SyscallNumber};
+
+    /// Trace every CPU, one buffer each
+    pub const MODE_CPU: u32 = 0;
+
+    /// Trace one thread into one buffer
+    pub const MODE_THREAD: u32 = 1;
+
+    /// Trace user code
+    pub const FLAG_USER: u32 = 1 << 0;
+
+    /// Trace kernel code
+    pub const FLAG_KERNEL: u32 = 1 << 1;
+
+    /// The buffer filled up and tracing stopped early
+    pub const BUFFER_FULL: u32 = 1 << 0;
+
+    /// `cpu` of the buffer in thread mode
+    pub const CPU_THREAD: u32 = u32::MAX;
+
+    /// Smallest and largest buffer sizes
+    pub const MIN_BUFFER_SIZE: u64 = 4096;
+    pub const MAX_BUFFER_SIZE: u64 = 64 * 1024 * 1024;
+
+    const ACTION_ALLOC: u64 = 0;
+    const ACTION_FREE: u64 = 1;
+    const ACTION_START: u64 = 2;
+    const ACTION_STOP: u64 = 3;
+
+    /// A tracing session
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Config {
+        /// `MODE_*`
+        pub mode: u32,
+
+        /// `FLAG_*` bits, 0 for both
+        pub flags: u32,
+
+        /// Size of each buffer in bytes, a power of two
+        pub buffer_size: u64,
+
+        /// Thread to trace in thread mode
+        pub thread: u64,
+    }
+
+    /// A stopped buffer
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct BufferInfo {
+        /// CPU, or `CPU_THREAD`
+        pub cpu: u32,
+
+        /// `BUFFER_*` bits
+        pub flags: u32,
+
+        /// Size of the buffer in bytes
+        pub size: u64,
+
+        /// Where the trace ends. Intel PT wraps, so there the newest
+        /// trace ends at `offset` and the oldest may follow it.
+        pub offset: u64,
+    }
+
+    fn control(resource: &Handle, action: u64, buffer: u64, size: usize) -> Result<()> {
+        let ret = unsafe {
+            syscall4(
+                SyscallNumber::ItraceControl as u64,
+                resource.raw() as u64,
+                action,
+                buffer,
+                size as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(())
+    }
+
+    /// Allocate a session's buffers. `resource` must be the root resource.
+    ///
+    /// Fails with `BadState` if a session is already allocated and
+    /// `NotSupported` if the CPU can't trace.
+    pub fn alloc(resource: &Handle, config: &Config) -> Result<()> {
+        control(
+            resource,
+            ACTION_ALLOC,
+            config as *const Config as u64,
+            core::mem::size_of::<Config>(),
+        )
+    }
+
+    /// Free the session's buffers; handed-over buffers stay valid
+    pub fn free(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_FREE, 0, 0)
+    }
+
+    /// Start tracing. A thread is traced from the next time it is scheduled.
+    pub fn start(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_START, 0, 0)
+    }
+
+    /// Stop tracing and flush the trace to the buffers
+    pub fn stop(resource: &Handle) -> Result<()> {
+        control(resource, ACTION_STOP, 0, 0)
+    }
+
+    /// Hand over buffer `index` of a stopped session
+    ///
+    /// `index` is the CPU in CPU mode and 0 in thread mode; past the last
+    /// buffer it fails.
+    pub fn buffer(resource: &Handle, index: u32) -> Result<(BufferInfo, Vmo)> {
+        let mut info = BufferInfo::default();
+        let mut vmo: u32 = 0;
+        unsafe {
+            let ret = syscall4(
+                SyscallNumber::ItraceGetBuffer as u64,
+                resource.raw() as u64,
+                index as u64,
+                &mut info as *mut BufferInfo as u64,
+                &mut vmo as *mut u32 as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+            Ok((info, Vmo::from_handle(Handle::from_raw(vmo, Vmo::DEFAULT_RIGHTS))))
+        }
+    }
+}
+
+/// EFI runtime services
+///
+/// Variables and the real-time clock of a system booted through the UEFI
+/// loader. Every call takes the root resource or an EFI resource; on
+/// other systems they fail with `NotSupported`.
+pub mod efi {
+    use super::*;
+    use crate::syscall::{syscall2, syscall4, syscall5, SyscallNumber};
+
+    /// Variable attributes
+    pub const VARIABLE_NON_VOLATILE: u32 = 1 << 0;
+    pub const VARIABLE_BOOTSERVICE_ACCESS: u32 = 1 << 1;
+    pub const VARIABLE_RUNTIME_ACCESS: u32 = 1 << 2;
+
+    /// Longest variable name, in UCS-2 characters
+    pub const VARIABLE_NAME_MAX: usize = 256;
+
+    /// Largest variable value
+    pub const VARIABLE_DATA_MAX: usize = 32 * 1024;
+
+    /// EFI_GUID in its in-memory byte order
+    pub type Guid = [u8; 16];
+
+    /// Vendor of the UEFI specification's global variables
+    pub const GLOBAL_VARIABLE: Guid = [
+        0x61, 0xdf, 0xe4, 0x8b, 0xca, 0x93, 0xd2, 0x11, 0xaa, 0x0d, 0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c,
+    ];
+
+    /// A variable to read or write
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Variable {
+        vendor: Guid,
+        attributes: u32,
+        name_len: u32,
+        name: u64,
+    }
+
+    /// EFI_TIME
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Time {
+        pub year: u16,
+        pub month: u8,
+        pub day: u8,
+        pub hour: u8,
+        pub minute: u8,
+        pub second: u8,
+        pub pad1: u8,
+        pub nanosecond: u32,
+        /// Minutes from UTC, or 2047 for local time
+        pub time_zone: i16,
+        pub daylight: u8,
+        pub pad2: u8,
+    }
+
+    fn variable(vendor: &Guid, name: &[u16], attributes: u32) -> Variable {
+        Variable {
+            vendor: *vendor,
+            attributes,
+            name_len: name.len() as u32,
+            name: name.as_ptr() as u64,
+        }
+    }
+
+    /// Read variable `name` (UCS-2, no NUL) of `vendor` into `data`
+    ///
+    /// Returns its attributes and size.
+    pub fn get_variable(resource: &Handle, vendor: &Guid, name: &[u16], data: &mut [u8]) -> Result<(u32, usize)> {
+        let mut var = variable(vendor, name, 0);
+        let mut actual: usize = 0;
+        unsafe {
+            let ret = syscall5(
+                SyscallNumber::EfiGetVariable as u64,
+                resource.raw() as u64,
+                &mut var as *mut Variable as u64,
+                data.as_mut_ptr() as u64,
+                data.len() as u64,
+                &mut actual as *mut usize as u64,
+            );
+
+            if (ret as i32) < 0 {
+                return Err(Error::from_raw(ret as i32));
+            }
+        }
+        Ok((var.attributes, actual))
+    }
+
+    /// Write variable `name` of `vendor`
+    pub fn set_variable(resource: &Handle, vendor: &Guid, name: &[u16], attributes: u32, data: &[u8]) -> Result<()> {
+        let var = variable(vendor, name, attributes);
+        let ret = unsafe {
+            syscall4(
+                SyscallNumber::EfiSetVariable as u64,
+                resource.raw() as u64,
+                &var as *const Variable as u64,
+                data.as_ptr() as u64,
+                data.len() as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(())
+    }
+
+    /// Delete variable `name` of `vendor`
+    pub fn delete_variable(resource: &Handle, vendor: &Guid, name: &[u16]) -> Result<()> {
+        set_variable(resource, vendor, name, 0, &[])
+    }
+
+    /// Read the real-time clock
+    pub fn get_time(resource: &Handle) -> Result<Time> {
+        let mut time = Time::default();
+        let ret = unsafe {
+            syscall2(
+                SyscallNumber::EfiGetTime as u64,
+                resource.raw() as u64,
+                &mut time as *mut Time as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(time)
+    }
+}
+
+/// Kernel debug console
+pub mod debug {
+    use super::*;
+    use crate::syscall::{syscall4, SyscallNumber};
+
+    /// Kernel status when nothing has been typed
+    const RX_ERR_SHOULD_WAIT: i32 = -18;
+
+    /// Read bytes typed on the debug console into `buf`
+    ///
+    /// Never blocks; returns 0 when nothing is waiting.
+    pub fn read(root_resource: &Handle, buf: &mut [u8]) -> Result<usize> {
+        let mut actual: usize = 0;
+        let ret = unsafe {
+            syscall4(
+                SyscallNumber::DebugRead as u64,
+                root_resource.raw() as u64,
+                buf.as_mut_ptr() as u64,
+                buf.len() as u64,
+                &mut actual as *mut usize as u64,
+            )
+        };
+
+        if ret as i32 == RX_ERR_SHOULD_WAIT {
+            return Ok(0);
+        }
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(actual)
+    }
+}
+
+/// Boot framebuffer
+///
+/// The linear framebuffer the UEFI loader set up. Map it with a physical
+/// VMO over `base`.
+pub mod framebuffer {
+    use super::*;
+    use crate::syscall::{syscall3, SyscallNumber};
+
+    /// Pixel formats, 32 bits per pixel
+    pub const FORMAT_RGB: u32 = 1;
+    pub const FORMAT_BGR: u32 = 2;
+
+    /// Stop the kernel's framebuffer console from drawing
+    pub const TAKE_OVER: u32 = 1 << 0;
+
+    /// Framebuffer description
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Info {
+        /// Physical base address
+        pub base: u64,
+        /// Size in bytes
+        pub size: u64,
+        pub width: u32,
+        pub height: u32,
+        /// Pixels per scan line
+        pub stride: u32,
+        pub format: u32,
+    }
+
+    /// Describe the framebuffer, with `TAKE_OVER` also claiming it
+    pub fn get_info(root_resource: &Handle, options: u32) -> Result<Info> {
+        let mut info = Info::default();
+        let ret = unsafe {
+            syscall3(
+                SyscallNumber::FramebufferGetInfo as u64,
+                root_resource.raw() as u64,
+                &mut info as *mut Info as u64,
+                options as u64,
+            )
+        };
+
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(info)
+    }
+}
+
+/// Hypervisor guests and virtual CPUs
+///
+/// A VMM creates a guest, backs its memory with VMOs, traps the ranges it
+/// emulates and runs each vCPU on its own thread, handling the packets
+/// `Vcpu::enter` returns.
+pub mod hypervisor {
+    use super::*;
+    use crate::syscall::{syscall1, syscall2, syscall3, syscall4, syscall6, SyscallNumber};
+
+    /// Guest memory is readable
+    pub const MAP_READ: u32 = 1 << 0;
+
+    /// Guest memory is writable
+    pub const MAP_WRITE: u32 = 1 << 1;
+
+    /// Guest memory is executable
+    pub const MAP_EXECUTE: u32 = 1 << 2;
+
+    /// Writes to the range queue a packet on a port; the guest keeps running
+    pub const TRAP_BELL: u32 = 0;
+
+    /// Accesses to the range exit to the VMM
+    pub const TRAP_MMIO: u32 = 1;
+
+    /// Accesses to the I/O port range exit to the VMM (x86)
+    pub const TRAP_IO: u32 = 2;
+
+    /// Writes to the range exit to the VMM, reads go to memory
+    pub const TRAP_WRITE: u32 = 3;
+
+    /// General-purpose register state
+    pub const VCPU_STATE: u32 = 0;
+
+    /// `Packet::packet_type` of a vCPU interrupt or exit
+    pub const PACKET_GUEST_VCPU: u32 = 4;
+
+    /// `Packet::packet_type` of an MMIO or write trap exit
+    pub const PACKET_GUEST_MEM: u32 = 7;
+
+    /// `Packet::packet_type` of an I/O port exit
+    pub const PACKET_GUEST_IO: u32 = 8;
+
+    const OP_MAP: u32 = 1;
+    const OP_UNMAP: u32 = 2;
+    const OP_FLAGS_SHIFT: u32 = 8;
+
+    /// An exit reported by `Vcpu::enter`, as a port packet
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Packet {
+        /// Trap key
+        pub key: u64,
+
+        /// One of the `PACKET_GUEST_*` types
+        pub packet_type: u32,
+
+        /// Status
+        pub status: i32,
+
+        /// Type-specific payload, see the kernel's guest packet layouts
+        pub payload: [u64; 4],
+    }
+
+    /// Registers, laid out as the kernel's `VcpuState`
+    ///
+    /// `regs[0..16]` are RAX, RCX, RDX, RBX, RSP, RBP, RSI, RDI and R8-R15,
+    /// then RIP, RFLAGS and CR3.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct VcpuState {
+        /// Registers
+        pub regs: [u64; 64],
+    }
+
+    impl Default for VcpuState {
+        fn default() -> Self {
+            Self { regs: [0; 64] }
+        }
+    }
+
+    fn check(ret: u64) -> Result<u64> {
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(ret)
+    }
+
+    /// A guest
+    pub struct Guest(Handle);
+
+    impl Guest {
+        /// Create a guest; `resource` must grant hypervisor access
+        pub fn create(resource: &Handle) -> Result<Self> {
+            let ret = check(unsafe {
+                syscall2(SyscallNumber::HypervisorCreate as u64, resource.raw() as u64, 0)
+            })?;
+            Ok(Self(unsafe { Handle::from_raw(ret as u32, Rights::DUPLICATE | Rights::TRANSFER | Rights::READ | Rights::WRITE) }))
+        }
+
+        /// Back `[addr, addr + len)` of guest-physical memory with `vmo`
+        /// from `vmo_offset`, with the `MAP_*` permissions in `flags`
+        pub fn map(&self, addr: u64, vmo: &Handle, vmo_offset: usize, len: usize, flags: u32) -> Result<()> {
+            check(unsafe {
+                syscall6(
+                    SyscallNumber::HypervisorOp as u64,
+                    self.0.raw() as u64,
+                    (OP_MAP | (flags << OP_FLAGS_SHIFT)) as u64,
+                    addr,
+                    vmo.raw() as u64,
+                    vmo_offset as u64,
+                    len as u64,
+                )
+            })?;
+            Ok(())
+        }
+
+        /// Remove the mappings covering `[addr, addr + len)`
+        pub fn unmap(&self, addr: u64, len: usize) -> Result<()> {
+            check(unsafe {
+                syscall6(
+                    SyscallNumber::HypervisorOp as u64,
+                    self.0.raw() as u64,
+                    OP_UNMAP as u64,
+                    addr,
+                    0,
+                    0,
+                    len as u64,
+                )
+            })?;
+            Ok(())
+        }
+
+        /// Trap `[addr, addr + len)` with a `TRAP_*` kind
+        ///
+        /// `port` is required for `TRAP_BELL` and must be `None` otherwise.
+        /// Packets for the trap carry `key`.
+        pub fn set_trap(&self, kind: u32, addr: u64, len: usize, port: Option<&Handle>, key: u64) -> Result<()> {
+            check(unsafe {
+                syscall6(
+                    SyscallNumber::GuestSetTrap as u64,
+                    self.0.raw() as u64,
+                    kind as u64,
+                    addr,
+                    len as u64,
+                    port.map_or(0, |port| port.raw() as u64),
+                    key,
+                )
+            })?;
+            Ok(())
+        }
+
+        /// Guest handle
+        pub fn handle(&self) -> &Handle {
+            &self.0
+        }
+    }
+
+    /// A virtual CPU
+    pub struct Vcpu(Handle);
+
+    impl Vcpu {
+        /// Create a vCPU in `guest` starting at `entry`
+        pub fn create(guest: &Guest, entry: u64) -> Result<Self> {
+            let ret = check(unsafe {
+                syscall3(SyscallNumber::VcpuCreate as u64, guest.0.raw() as u64, 0, entry)
+            })?;
+            Ok(Self(unsafe { Handle::from_raw(ret as u32, Rights::DUPLICATE | Rights::TRANSFER | Rights::READ | Rights::WRITE) }))
+        }
+
+        /// Run the vCPU until it exits to the VMM
+        pub fn enter(&self) -> Result<Packet> {
+            let mut packet = Packet::default();
+            check(unsafe {
+                syscall2(
+                    SyscallNumber::VcpuEnter as u64,
+                    self.0.raw() as u64,
+                    &mut packet as *mut Packet as u64,
+                )
+            })?;
+            Ok(packet)
+        }
+
+        /// Make the vCPU exit to the VMM with an interrupt packet
+        pub fn kick(&self) -> Result<()> {
+            check(unsafe { syscall1(SyscallNumber::VcpuKick as u64, self.0.raw() as u64) })?;
+            Ok(())
+        }
+
+        /// Raise interrupt `vector` in the vCPU
+        pub fn interrupt(&self, vector: u32) -> Result<()> {
+            check(unsafe {
+                syscall2(SyscallNumber::VcpuInterrupt as u64, self.0.raw() as u64, vector as u64)
+            })?;
+            Ok(())
+        }
+
+        /// Read the registers; fails while the vCPU is running
+        pub fn read_state(&self) -> Result<VcpuState> {
+            let mut state = VcpuState::default();
+            check(unsafe {
+                syscall4(
+                    SyscallNumber::VcpuReadState as u64,
+                    self.0.raw() as u64,
+                    VCPU_STATE as u64,
+                    &mut state as *mut VcpuState as u64,
+                    core::mem::size_of::<VcpuState>() as u64,
+                )
+            })?;
+            Ok(state)
+        }
+
+        /// Write the registers; fails while the vCPU is running
+        pub fn write_state(&self, state: &VcpuState) -> Result<()> {
+            check(unsafe {
+                syscall4(
+                    SyscallNumber::VcpuWriteState as u64,
+                    self.0.raw() as u64,
+                    VCPU_STATE as u64,
+                    state as *const VcpuState as u64,
+                    core::mem::size_of::<VcpuState>() as u64,
+                )
+            })?;
+            Ok(())
+        }
+    }
+}
+
+/// Clocks and UTC
+///
+/// The kernel keeps only monotonic time. UTC comes from a clock object a
+/// time daemon disciplines; each process reads its own UTC reference,
+/// normally the system UTC clock.
+pub mod clock {
+    use super::*;
+    use crate::syscall::{syscall0, syscall1, syscall2, syscall3, SyscallNumber};
+
+    /// `Clock::create` option: never steps backwards
+    pub const OPT_MONOTONIC: u64 = 1 << 0;
+
+    /// `Clock::create` option: never steps; implies `OPT_MONOTONIC`
+    pub const OPT_CONTINUOUS: u64 = 1 << 1;
+
+    /// `Clock::create` option: start equal to the monotonic clock
+    pub const OPT_AUTO_START: u64 = 1 << 2;
+
+    /// `ClockUpdate::flags`: step to `value`
+    pub const UPDATE_VALUE: u32 = 1 << 0;
+
+    /// `ClockUpdate::flags`: set the rate to `rate_adjust_ppm`
+    pub const UPDATE_RATE: u32 = 1 << 1;
+
+    /// `ClockUpdate::flags`: set the error bound
+    pub const UPDATE_ERROR_BOUND: u32 = 1 << 2;
+
+    /// `ClockUpdate::flags`: slew by `slew_offset`
+    pub const UPDATE_SLEW: u32 = 1 << 3;
+
+    /// Error bound of a clock nobody has estimated
+    pub const ERROR_BOUND_UNKNOWN: u64 = u64::MAX;
+
+    const CLOCK_MONOTONIC: u64 = 0;
+    const CLOCK_UTC: u64 = 1;
+
+    /// Maps monotonic time to a clock's time
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Transform {
+        /// Monotonic time the transform is anchored at
+        pub reference_offset: i64,
+
+        /// Clock time at `reference_offset`
+        pub synthetic_offset: i64,
+
+        /// Clock nanoseconds per `reference_ticks`
+        pub synthetic_ticks: u32,
+
+        /// Monotonic nanoseconds per `synthetic_ticks`
+        pub reference_ticks: u32,
+    }
+
+    impl Transform {
+        /// Clock time at monotonic time `reference`
+        pub fn apply(&self, reference: i64) -> i64 {
+            let elapsed = reference as i128 - self.reference_offset as i128;
+            let scaled = elapsed * self.synthetic_ticks as i128 / self.reference_ticks as i128;
+            (self.synthetic_offset as i128 + scaled) as i64
+        }
+    }
+
+    /// Arguments of `Clock::update`
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ClockUpdate {
+        /// `UPDATE_*` bits
+        pub flags: u32,
+
+        /// New rate, up to 1000 ppm either way
+        pub rate_adjust_ppm: i32,
+
+        /// New value
+        pub value: i64,
+
+        /// Offset to slew by
+        pub slew_offset: i64,
+
+        /// New error bound
+        pub error_bound: u64,
+    }
+
+    /// A clock's transforms and update history, laid out as the kernel's
+    /// `ClockDetails`
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ClockDetails {
+        /// Creation options
+        pub options: u64,
+
+        /// Earliest time the clock reads
+        pub backstop_time: i64,
+
+        /// Bumped by every update
+        pub generation_counter: u32,
+
+        /// Reserved
+        pub padding: u32,
+
+        /// Error bound, or `ERROR_BOUND_UNKNOWN`
+        pub error_bound: u64,
+
+        /// Monotonic time of the query
+        pub query_reference: i64,
+
+        /// Transform in effect
+        pub reference_to_synthetic: Transform,
+
+        /// Monotonic time a slew in progress ends, or 0
+        pub slew_end_reference: i64,
+
+        /// Transform once the slew ends
+        pub after_slew: Transform,
+
+        /// Monotonic time of the last value update, or 0
+        pub last_value_update: i64,
+
+        /// Monotonic time of the last rate update, or 0
+        pub last_rate_adjust_update: i64,
+
+        /// Monotonic time of the last error bound update, or 0
+        pub last_error_bound_update: i64,
+    }
+
+    fn check(ret: u64) -> Result<u64> {
+        if (ret as i32) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(ret)
+    }
+
+    /// Nanoseconds since boot
+    pub fn monotonic() -> i64 {
+        unsafe { syscall1(SyscallNumber::ClockGet as u64, CLOCK_MONOTONIC) as i64 }
+    }
+
+    /// The current UTC time, in nanoseconds since the Unix epoch
+    pub fn utc() -> Result<i64> {
+        check(unsafe { syscall1(SyscallNumber::ClockGet as u64, CLOCK_UTC) }).map(|now| now as i64)
+    }
+
+    /// A clock object
+    pub struct Clock(Handle);
+
+    impl Clock {
+        /// Create a clock with `OPT_*` options that never reads less than
+        /// `backstop`
+        pub fn create(options: u64, backstop: i64) -> Result<Self> {
+            let mut handle = 0u32;
+            check(unsafe {
+                syscall3(
+                    SyscallNumber::ClockCreate as u64,
+                    options,
+                    backstop as u64,
+                    &mut handle as *mut u32 as u64,
+                )
+            })?;
+            Ok(Self(unsafe { Handle::from_raw(handle, Rights::DUPLICATE | Rights::TRANSFER | Rights::READ | Rights::WRITE) }))
+        }
+
+        /// The clock this process reads as UTC
+        pub fn utc_reference() -> Result<Self> {
+            let ret = check(unsafe { syscall0(SyscallNumber::UtcReferenceGet as u64) })?;
+            Ok(Self(unsafe { Handle::from_raw(ret as u32, Rights::DUPLICATE | Rights::TRANSFER | Rights::READ | Rights::WRITE) }))
+        }
+
+        /// Make `clock` this process's UTC clock, and that of the
+        /// processes it creates from now on; `None` goes back to the
+        /// system clock. `resource` must be the root resource.
+        pub fn swap_utc_reference(resource: &Handle, clock: Option<&Clock>) -> Result<()> {
+            check(unsafe {
+                syscall3(
+                    SyscallNumber::UtcReferenceSwap as u64,
+                    resource.raw() as u64,
+                    clock.map_or(0, |clock| clock.0.raw()) as u64,
+                    0,
+                )
+            })?;
+            Ok(())
+        }
+
+        /// Read the clock
+        pub fn read(&self) -> Result<i64> {
+            let mut now = 0i64;
+            check(unsafe {
+                syscall2(SyscallNumber::ClockRead as u64, self.0.raw() as u64, &mut now as *mut i64 as u64)
+            })?;
+            Ok(now)
+        }
+
+        /// The clock's transforms and update history
+        pub fn details(&self) -> Result<ClockDetails> {
+            let mut details = ClockDetails::default();
+            check(unsafe {
+                syscall3(
+                    SyscallNumber::ClockGetDetails as u64,
+                    self.0.raw() as u64,
+                    &mut details as *mut ClockDetails as u64,
+                    core::mem::size_of::<ClockDetails>() as u64,
+                )
+            })?;
+            Ok(details)
+        }
+
+        /// Step, rate-adjust or slew the clock
+        pub fn update(&self, update: &ClockUpdate) -> Result<()> {
+            check(unsafe {
+                syscall3(
+                    SyscallNumber::ClockUpdate as u64,
+                    self.0.raw() as u64,
+                    update as *const ClockUpdate as u64,
+                    core::mem::size_of::<ClockUpdate>() as u64,
+                )
+            })?;
+            Ok(())
+        }
+    }
+}
+
+/// Exception channels and the state of excepting threads
+///
+/// A handler bound to a job is sent an [`ExceptionReport`] for each fatal
+/// exception in a process under the job, unless a job nearer the process
+/// has its own handler. The thread that took the exception waits until
+/// the handler resumes it, then dies with its process.
+pub mod exception {
+    use super::*;
+    use crate::syscall::{syscall2, syscall4, SyscallNumber};
+
+    /// Hardware breakpoint, watchpoint or single step; `subtype` says which
+    pub const EXCP_HW_BREAKPOINT: u32 = 0x1;
+
+    /// Software breakpoint
+    pub const EXCP_SW_BREAKPOINT: u32 = 0x2;
+
+    /// Undefined instruction
+    pub const EXCP_UNDEFINED_INSTRUCTION: u32 = 0x4;
+
+    /// Any other fault
+    pub const EXCP_GENERAL: u32 = 0x8;
+
+    /// Page fault the kernel couldn't resolve
+    pub const EXCP_FATAL_PAGE_FAULT: u32 = 0x20;
+
+    /// [`EXCP_HW_BREAKPOINT`] subtype: single step
+    pub const HW_BREAKPOINT_SINGLE_STEP: u32 = 1;
+
+    /// [`EXCP_HW_BREAKPOINT`] subtype: instruction breakpoint
+    pub const HW_BREAKPOINT_INSTRUCTION: u32 = 2;
+
+    /// [`EXCP_HW_BREAKPOINT`] subtype: data watchpoint
+    pub const HW_BREAKPOINT_WATCHPOINT: u32 = 3;
+
+    /// `read_state` kind: [`GeneralRegs`]
+    pub const STATE_GENERAL_REGS: u32 = 0;
+
+    /// `read_state` kind: the stack snapshot, starting at `sp`
+    pub const STATE_STACK: u32 = 1;
+
+    /// `read_state` and `write_state` kind: [`DebugRegs`]
+    pub const STATE_DEBUG_REGS: u32 = 2;
+
+    /// Most hardware breakpoints [`DebugRegs`] holds
+    pub const MAX_HW_BREAKPOINTS: usize = 4;
+
+    /// Most bytes of stack the kernel saves at an exception
+    pub const STACK_SNAPSHOT_SIZE: usize = 8192;
+
+    /// [`write_coredump`] option: save every readable mapping, not only
+    /// writable ones and the page at the pc
+    pub const COREDUMP_ALL_MEMORY: u32 = 1;
+
+    /// Registers at the exception, laid out as the kernel's `GeneralRegs`
+    ///
+    /// `regs` holds the general registers in DWARF order (x86_64: rax,
+    /// rdx, rcx, rbx, rsi, rdi, rbp, rsp, r8-r15; aarch64: x0-x30, sp;
+    /// riscv64: x0-x31).
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct GeneralRegs {
+        /// Program counter
+        pub pc: u64,
+
+        /// Stack pointer
+        pub sp: u64,
+
+        /// Frame pointer
+        pub fp: u64,
+
+        /// Flags register
+        pub flags: u64,
+
+        /// General registers
+        pub regs: [u64; 32],
+    }
+
+    /// Hardware instruction breakpoints of a thread, laid out as the
+    /// kernel's `DebugRegs`
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct DebugRegs {
+        /// Breakpoint addresses
+        pub hw_bps: [u64; MAX_HW_BREAKPOINTS],
+
+        /// Bit `i` set: `hw_bps[i]` is armed
+        pub enabled: u32,
+
+        /// Breakpoints the CPU has for user threads; ignored on write
+        pub count: u32,
+
+        /// DR6 at the last debug exception on x86_64; ignored on write
+        pub status: u64,
+    }
+
+    /// Message a handler reads for each exception
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ExceptionReport {
+        /// `EXCP_*` kind
+        pub kind: u32,
+
+        /// `HW_BREAKPOINT_*` for [`EXCP_HW_BREAKPOINT`], otherwise 0
+        pub subtype: u32,
+
+        /// Process ID, also its handle value
+        pub pid: u64,
+
+        /// Thread ID, also its handle value
+        pub tid: u64,
+
+        /// Process koid
+        pub process_koid: u64,
+
+        /// Thread koid
+        pub thread_koid: u64,
+
+        /// Program counter
+        pub pc: u64,
+
+        /// Stack pointer
+        pub sp: u64,
+
+        /// Frame pointer
+        pub fp: u64,
+
+        /// Faulting address for page faults, otherwise 0
+        pub fault_addr: u64,
+
+        /// Architecture error code
+        pub err_code: u64,
+
+        /// For breakpoints and single steps, the status register the kernel
+        /// decoded: DR6 on x86_64, ESR_EL1 on aarch64, scause on riscv64
+        pub arch_status: u64,
+    }
+
+    impl ExceptionReport {
+        /// Decode a message read from an exception channel
+        pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+            if bytes.len() != core::mem::size_of::<Self>() {
+                return None;
+            }
+            Some(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
+        }
+
+        /// Name of the exception kind
+        pub fn kind_name(&self) -> &'static str {
+            match (self.kind, self.subtype) {
+                (EXCP_HW_BREAKPOINT, HW_BREAKPOINT_SINGLE_STEP) => "single step",
+                (EXCP_HW_BREAKPOINT, HW_BREAKPOINT_WATCHPOINT) => "watchpoint",
+                (EXCP_HW_BREAKPOINT, _) => "hw breakpoint",
+                (EXCP_SW_BREAKPOINT, _) => "sw breakpoint",
+                (EXCP_UNDEFINED_INSTRUCTION, _) => "undefined instruction",
+                (EXCP_GENERAL, _) => "general fault",
+                (EXCP_FATAL_PAGE_FAULT, _) => "page fault",
+                _ => "unknown exception",
+            }
+        }
+    }
+
+    fn check(ret: u64) -> Result<u64> {
+        if (ret as i64) < 0 {
+            return Err(Error::from_raw(ret as i32));
+        }
+        Ok(ret)
+    }
+
+    /// Bind an exception channel to `job`, returning the handler's end
+    ///
+    /// Handle 0 is the root job. Fails with `AlreadyExists` if the job
+    /// already has a handler.
+    pub fn create_channel(job: &Handle) -> Result<Handle> {
+        let ret = check(unsafe {
+            syscall2(SyscallNumber::TaskCreateExceptionChannel as u64, job.raw() as u64, 0)
+        })?;
+        Ok(unsafe { Handle::from_raw(ret as u32, Channel::DEFAULT_RIGHTS) })
+    }
+
+    /// Read state of kind `kind` of a thread waiting for its handler
+    ///
+    /// Returns the number of bytes written to `buf`. Fails with `BadState`
+    /// if the thread isn't waiting, except for [`STATE_DEBUG_REGS`].
+    pub fn read_state(thread: &Handle, kind: u32, buf: &mut [u8]) -> Result<usize> {
+        let ret = check(unsafe {
+            syscall4(
+                SyscallNumber::ThreadReadState as u64,
+                thread.raw() as u64,
+                kind as u64,
+                buf.as_mut_ptr() as u64,
+                buf.len() as u64,
+            )
+        })?;
+        Ok(ret as usize)
+    }
+
+    /// Registers of a thread waiting for its handler
+    pub fn read_regs(thread: &Handle) -> Result<GeneralRegs> {
+        let mut regs = GeneralRegs::default();
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(
+                &mut regs as *mut GeneralRegs as *mut u8,
+                core::mem::size_of::<GeneralRegs>(),
+            )
+        };
+        read_state(thread, STATE_GENERAL_REGS, buf)?;
+        Ok(regs)
+    }
+
+    /// Stack of a thread waiting for its handler, from its `sp` up
+    pub fn read_stack(thread: &Handle, buf: &mut [u8]) -> Result<usize> {
+        read_state(thread, STATE_STACK, buf)
+    }
+
+    /// Hardware breakpoints of a thread, waiting or not
+    pub fn read_debug_regs(thread: &Handle) -> Result<DebugRegs> {
+        let mut regs = DebugRegs::default();
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(
+                &mut regs as *mut DebugRegs as *mut u8,
+                core::mem::size_of::<DebugRegs>(),
+            )
+        };
+        read_state(thread, STATE_DEBUG_REGS, buf)?;
+        Ok(regs)
+    }
+
+    /// Replace the hardware breakpoints of a thread
+    ///
+    /// They take effect the next time the thread is scheduled. Needs
+    /// `kernel.enable-debugging-syscalls=true`, and hardware breakpoints
+    /// the kernel can use; fails with `NotSupported` otherwise.
+    pub fn write_debug_regs(thread: &Handle, regs: &DebugRegs) -> Result<()> {
+        check(unsafe {
+            syscall4(
+                SyscallNumber::ThreadWriteState as u64,
+                thread.raw() as u64,
+                STATE_DEBUG_REGS as u64,
+                regs as *const DebugRegs as u64,
+                core::mem::size_of::<DebugRegs>() as u64,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Dump the process of a thread waiting for its handler into a VMO
+    ///
+    /// The dump's format is in the syscall ABI spec; its header gives the
+    /// size in bytes. Fails with `BadState` if the thread isn't waiting.
+    pub fn write_coredump(thread: &Handle, options: u32) -> Result<Vmo> {
+        let ret = check(unsafe {
+            syscall2(SyscallNumber::ThreadWriteCoredump as u64, thread.raw() as u64, options as u64)
+        })?;
+        Ok(unsafe { Vmo::from_handle(Handle::from_raw(ret as u32, Vmo::DEFAULT_RIGHTS)) })
+    }
+
+    /// Let a thread waiting for its handler go on to die
+    pub fn resume(thread: &Handle) -> Result<()> {
+        check(unsafe { syscall2(SyscallNumber::ThreadResume as u64, thread.raw() as u64, 0) })?;
+        Ok(())
     }
 }
@@ -0,0 +1,224 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! VMO Mappings
+//!
+//! [`Vmo::map`] maps part of a VMO into a VMAR and returns a [`Mapping`]
+//! that unmaps it when dropped, so callers don't have to pair
+//! [`vmar::map`](crate::vmar::map) and [`vmar::unmap`](crate::vmar::unmap)
+//! by hand.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let root = vmar::root_self()?;
+//! let vmo = Vmo::create(8192, None)?;
+//!
+//! let mut mapping = vmo.map(&root, 0, 8192, MapPerms::READ | MapPerms::WRITE)?;
+//! mapping.as_mut_slice()[..5].copy_from_slice(b"hello");
+//!
+//! // Drop the second page, then make the rest read-only
+//! mapping.unmap(4096, 4096)?;
+//! mapping.protect(MapPerms::READ)?;
+//! ```
+
+#![no_std]
+
+use bitflags::bitflags;
+use crate::error::{Error, Result, Status};
+use crate::handles::{Handle, Vmo};
+use crate::vmar;
+
+/// Mappings are made and trimmed in whole pages
+const PAGE_SIZE: usize = 4096;
+
+bitflags! {
+    /// Access permissions of a mapping
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MapPerms: u32 {
+        /// Readable
+        const READ = 0x01;
+        /// Writable
+        const WRITE = 0x02;
+        /// Executable
+        const EXECUTE = 0x04;
+    }
+}
+
+/// A mapped range of a VMO, unmapped on drop
+///
+/// The mapping stays valid after the VMO handle is closed; it borrows the
+/// VMAR it lives in.
+#[derive(Debug)]
+pub struct Mapping<'a> {
+    /// VMAR holding the mapping
+    vmar: &'a Handle,
+    /// Base address
+    addr: usize,
+    /// Length in bytes, a multiple of the page size
+    len: usize,
+    /// Current permissions
+    perms: MapPerms,
+}
+
+impl Vmo {
+    /// Map `len` bytes of this VMO, starting at `offset`, into `vmar`
+    ///
+    /// `offset` and `len` must be page-aligned and `len` nonzero.
+    pub fn map<'a>(&self, vmar: &'a Handle, offset: u64, len: usize, perms: MapPerms) -> Result<Mapping<'a>> {
+        if len == 0 || len % PAGE_SIZE != 0 || offset % PAGE_SIZE as u64 != 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let addr = vmar::map(vmar, 0, self, offset, len, perms.bits())?;
+        Ok(Mapping { vmar, addr, len, perms })
+    }
+}
+
+impl<'a> Mapping<'a> {
+    /// Base address of the mapping
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Length of the mapping in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the mapping is empty; it never is while it exists
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Current permissions
+    pub fn perms(&self) -> MapPerms {
+        self.perms
+    }
+
+    /// View the mapped memory
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mapping isn't readable.
+    pub fn as_slice(&self) -> &[u8] {
+        assert!(self.perms.contains(MapPerms::READ), "mapping is not readable");
+        unsafe { core::slice::from_raw_parts(self.addr as *const u8, self.len) }
+    }
+
+    /// View the mapped memory mutably
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mapping isn't readable and writable.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        assert!(
+            self.perms.contains(MapPerms::READ | MapPerms::WRITE),
+            "mapping is not writable"
+        );
+        unsafe { core::slice::from_raw_parts_mut(self.addr as *mut u8, self.len) }
+    }
+
+    /// Change the permissions of the whole mapping
+    pub fn protect(&mut self, perms: MapPerms) -> Result<()> {
+        vmar::protect(self.vmar, self.addr, self.len, perms.bits())?;
+        self.perms = perms;
+        Ok(())
+    }
+
+    /// Unmap `[offset, offset + len)` of the mapping
+    ///
+    /// The range must be page-aligned and at the start or end of the
+    /// mapping, so that what remains is contiguous. Unmapping all of it
+    /// fails with `InvalidArgs`; drop the mapping instead.
+    pub fn unmap(&mut self, offset: usize, len: usize) -> Result<()> {
+        let addr = self.trimmed(offset, len)?;
+        vmar::unmap(self.vmar, self.addr + offset, len)?;
+        self.addr = addr;
+        self.len -= len;
+        Ok(())
+    }
+
+    /// Base address left after unmapping `[offset, offset + len)`
+    fn trimmed(&self, offset: usize, len: usize) -> Result<usize> {
+        if len == 0 || len >= self.len || offset % PAGE_SIZE != 0 || len % PAGE_SIZE != 0 {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        if offset == 0 {
+            Ok(self.addr + len)
+        } else if offset.checked_add(len) == Some(self.len) {
+            Ok(self.addr)
+        } else {
+            Err(Error::new(Status::InvalidArgs))
+        }
+    }
+}
+
+impl<'a> Drop for Mapping<'a> {
+    fn drop(&mut self) {
+        let _ = vmar::unmap(self.vmar, self.addr, self.len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::ManuallyDrop;
+
+    /// Stands in for a VMAR; no syscall ever reaches it
+    static VMAR: Handle = Handle::INVALID;
+
+    /// A mapping of `[addr, addr + len)` that was never made through a VMAR
+    ///
+    /// It's never dropped, so no unmap reaches the kernel.
+    fn fake_mapping(addr: usize, len: usize, perms: MapPerms) -> ManuallyDrop<Mapping<'static>> {
+        ManuallyDrop::new(Mapping { vmar: &VMAR, addr, len, perms })
+    }
+
+    #[test]
+    fn test_map_rejects_unaligned_ranges() {
+        let vmo = unsafe { Vmo::from_handle(Handle::INVALID) };
+        let perms = MapPerms::READ;
+        for (offset, len) in [(0, 0), (0, 100), (100, PAGE_SIZE)] {
+            let err = vmo.map(&VMAR, offset, len, perms).unwrap_err();
+            assert_eq!(err.status(), Status::InvalidArgs);
+        }
+    }
+
+    #[test]
+    fn test_trimmed() {
+        let mapping = fake_mapping(0x10000, 3 * PAGE_SIZE, MapPerms::READ);
+
+        // Either end may go, leaving the rest contiguous
+        assert_eq!(mapping.trimmed(0, PAGE_SIZE).ok(), Some(0x10000 + PAGE_SIZE));
+        assert_eq!(mapping.trimmed(PAGE_SIZE, 2 * PAGE_SIZE).ok(), Some(0x10000));
+
+        // Not the middle, not everything and not partial pages
+        assert!(mapping.trimmed(PAGE_SIZE, PAGE_SIZE).is_err());
+        assert!(mapping.trimmed(0, 3 * PAGE_SIZE).is_err());
+        assert!(mapping.trimmed(0, 0).is_err());
+        assert!(mapping.trimmed(0, 100).is_err());
+        assert!(mapping.trimmed(2 * PAGE_SIZE + 1, PAGE_SIZE - 1).is_err());
+    }
+
+    #[test]
+    fn test_slices_follow_perms() {
+        let mut buf = [7u8; 16];
+        let mut mapping = fake_mapping(buf.as_mut_ptr() as usize, buf.len(), MapPerms::READ | MapPerms::WRITE);
+        mapping.as_mut_slice()[0] = 1;
+        assert_eq!(&mapping.as_slice()[..2], &[1, 7]);
+        assert_eq!(mapping.len(), 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "mapping is not writable")]
+    fn test_read_only_mapping_is_not_writable() {
+        let mut buf = [0u8; 16];
+        let mut mapping = fake_mapping(buf.as_mut_ptr() as usize, buf.len(), MapPerms::READ);
+        let _ = mapping.as_mut_slice();
+    }
+}
@@ -0,0 +1,378 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! One-time initialization
+//!
+//! This module provides [`Once`], [`OnceCell`] and [`Lazy`] for safely
+//! initializing globals from any thread.
+//!
+//! A `Once` is a futex word. The first caller moves it to running and runs
+//! the initializer; later callers sleep on the futex until it completes.
+//! If the initializer panics the `Once` is poisoned: waiters are woken and
+//! every later [`Once::call_once`] panics, while [`Once::call_once_force`]
+//! may retry.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use libsys::once::Lazy;
+//!
+//! static PAGE_SIZE: Lazy<usize> = Lazy::new(|| query_page_size());
+//!
+//! fn page_size() -> usize {
+//!     *PAGE_SIZE
+//! }
+//! ```
+
+#![no_std]
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::futex;
+
+/// Once state values
+const ONCE_INCOMPLETE: u32 = 0;
+const ONCE_POISONED: u32 = 1;
+const ONCE_RUNNING: u32 = 2;
+/// Running, with threads sleeping on the futex
+const ONCE_QUEUED: u32 = 3;
+const ONCE_COMPLETE: u32 = 4;
+
+/// State passed to [`Once::call_once_force`] initializers
+#[derive(Debug)]
+pub struct OnceState {
+    poisoned: bool,
+}
+
+impl OnceState {
+    /// Whether an earlier initializer panicked
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+}
+
+/// A one-time initialization primitive
+///
+/// Runs an initializer exactly once, even when many threads race to call
+/// it. Callers that lose the race block until it has finished.
+#[repr(C)]
+pub struct Once {
+    /// The once state (incomplete, poisoned, running, queued or complete)
+    state: AtomicU32,
+}
+
+// Once is Send and Sync
+unsafe impl Send for Once {}
+unsafe impl Sync for Once {}
+
+impl Once {
+    /// Create a new `Once`
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(ONCE_INCOMPLETE),
+        }
+    }
+
+    /// Whether an initializer has completed
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == ONCE_COMPLETE
+    }
+
+    /// Run `f` if no initializer has completed yet
+    ///
+    /// Blocks while another thread runs its initializer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Once` is poisoned.
+    pub fn call_once(&self, f: impl FnOnce()) {
+        if self.is_completed() {
+            return;
+        }
+
+        let mut f = Some(f);
+        self.call(false, &mut |_| (f.take().unwrap())());
+    }
+
+    /// Run `f` if no initializer has completed yet, even if poisoned
+    ///
+    /// `f` can tell from its [`OnceState`] whether an earlier initializer
+    /// panicked. If `f` returns, the `Once` completes.
+    pub fn call_once_force(&self, f: impl FnOnce(&OnceState)) {
+        if self.is_completed() {
+            return;
+        }
+
+        let mut f = Some(f);
+        self.call(true, &mut |state| (f.take().unwrap())(state));
+    }
+
+    /// Slow path shared by `call_once` and `call_once_force`
+    #[cold]
+    fn call(&self, ignore_poison: bool, f: &mut dyn FnMut(&OnceState)) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            match state {
+                ONCE_COMPLETE => return,
+                ONCE_POISONED if !ignore_poison => {
+                    panic!("Once instance has previously been poisoned");
+                }
+                ONCE_INCOMPLETE | ONCE_POISONED => {
+                    if let Err(actual) = self.state.compare_exchange(
+                        state,
+                        ONCE_RUNNING,
+                        Ordering::Acquire,
+                        Ordering::Acquire,
+                    ) {
+                        state = actual;
+                        continue;
+                    }
+
+                    // Poisons the Once if `f` unwinds
+                    let mut guard = CompletionGuard {
+                        state: &self.state,
+                        set_on_drop: ONCE_POISONED,
+                    };
+                    f(&OnceState {
+                        poisoned: state == ONCE_POISONED,
+                    });
+                    guard.set_on_drop = ONCE_COMPLETE;
+                    return;
+                }
+                ONCE_RUNNING => {
+                    // Ask the runner to wake us when it finishes
+                    if let Err(actual) = self.state.compare_exchange(
+                        ONCE_RUNNING,
+                        ONCE_QUEUED,
+                        Ordering::Relaxed,
+                        Ordering::Acquire,
+                    ) {
+                        state = actual;
+                        continue;
+                    }
+                    state = ONCE_QUEUED;
+                }
+                _ => {
+                    futex::wait(&self.state, ONCE_QUEUED);
+                    state = self.state.load(Ordering::Acquire);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Publishes the initializer's outcome and wakes waiters when dropped
+struct CompletionGuard<'a> {
+    state: &'a AtomicU32,
+    set_on_drop: u32,
+}
+
+impl<'a> Drop for CompletionGuard<'a> {
+    fn drop(&mut self) {
+        if self.state.swap(self.set_on_drop, Ordering::Release) == ONCE_QUEUED {
+            futex::wake(self.state, futex::WAKE_ALL);
+        }
+    }
+}
+
+/// A cell that can be written only once
+///
+/// Reads after initialization are a single atomic load.
+pub struct OnceCell<T> {
+    once: Once,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// The value is only written by the thread running the Once, before any
+// reader can see it
+unsafe impl<T: Send> Send for OnceCell<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    /// Create an empty cell
+    pub const fn new() -> Self {
+        Self {
+            once: Once::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Get the value, if the cell has been initialized
+    pub fn get(&self) -> Option<&T> {
+        if self.once.is_completed() {
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Set the value if the cell is empty
+    ///
+    /// Returns `Err(value)` if the cell was already initialized.
+    pub fn set(&self, value: T) -> core::result::Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    /// Get the value, initializing it with `f` if the cell is empty
+    ///
+    /// Concurrent callers block until one of them has run its `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an earlier initializer panicked.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        self.once.call_once(|| unsafe {
+            (*self.value.get()).write(f());
+        });
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            unsafe { (*self.value.get()).assume_init_drop() };
+        }
+    }
+}
+
+/// A value initialized on first access
+///
+/// The `lazy_static` pattern for `static` items: `init` runs the first
+/// time the value is dereferenced.
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+// `init` is only taken by the thread running the cell's Once
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Create a value that will be initialized by `init`
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    /// Force initialization and get the value
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| match unsafe { (*this.init.get()).take() } {
+            Some(init) => init(),
+            None => panic!("Lazy instance has previously been poisoned"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_once_runs_once() {
+        let once = Once::new();
+        let mut runs = 0;
+        assert!(!once.is_completed());
+        once.call_once(|| runs += 1);
+        once.call_once(|| runs += 1);
+        assert_eq!(runs, 1);
+        assert!(once.is_completed());
+    }
+
+    #[test]
+    fn test_once_poisoned_by_panic() {
+        let once = Once::new();
+        assert!(std::panic::catch_unwind(|| once.call_once(|| panic!("init failed"))).is_err());
+        assert!(!once.is_completed());
+
+        // call_once refuses a poisoned Once; call_once_force may retry
+        assert!(std::panic::catch_unwind(|| once.call_once(|| {})).is_err());
+        let mut poisoned = false;
+        once.call_once_force(|state| poisoned = state.is_poisoned());
+        assert!(poisoned);
+        assert!(once.is_completed());
+    }
+
+    #[test]
+    fn test_once_cell_set() {
+        let cell = OnceCell::new();
+        assert_eq!(cell.get(), None);
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(cell.get_or_init(|| 3), &1);
+        assert_eq!(cell.get(), Some(&1));
+    }
+
+    #[test]
+    fn test_once_cell_drops_value() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        drop(OnceCell::<Counted>::new());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+
+        let cell = OnceCell::new();
+        cell.get_or_init(|| Counted);
+        drop(cell);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_lazy_initializes_on_first_use() {
+        static INITS: AtomicUsize = AtomicUsize::new(0);
+        static VALUE: Lazy<u32> = Lazy::new(|| {
+            INITS.fetch_add(1, Ordering::Relaxed);
+            42
+        });
+
+        assert_eq!(INITS.load(Ordering::Relaxed), 0);
+        assert_eq!(*VALUE, 42);
+        assert_eq!(*Lazy::force(&VALUE), 42);
+        assert_eq!(INITS.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Startup Handles
+//!
+//! The handles a process is started with. Each comes with an info word
+//! whose low byte is its type (see [`handle_type`]) and whose high half
+//! depends on the type, such as a descriptor number. The C runtime records
+//! them with [`init`] before `main`; code then takes the ones it needs with
+//! [`take`], and each handle can be taken once.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let root = libsys::startup::root_resource()?;
+//! let uart = libsys::resource::create(&root, libsys::resource::KIND_IOPORT, 0x3f8, 8, "uart")?;
+//! ```
+
+#![no_std]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::error::{Error, Result, Status};
+use crate::handles::{Handle, Rights};
+use crate::once::OnceCell;
+
+/// Startup handle types, the low byte of a startup handle's info word
+pub mod handle_type {
+    /// The namespace root, a channel to a filesystem server
+    pub const NS_ROOT: u32 = 0x01;
+
+    /// The root resource, given to the first process only
+    pub const RESOURCE: u32 = 0x02;
+
+    /// A VMO to open as the descriptor in the info word's high half
+    pub const FD_VMO: u32 = 0x10;
+
+    /// A channel to open as the descriptor in the info word's high half
+    pub const FD_CHANNEL: u32 = 0x11;
+}
+
+/// Most startup handles a process keeps; the rest are closed
+pub const MAX_STARTUP_HANDLES: usize = 32;
+
+/// Raw value of an empty or taken slot
+const NO_HANDLE: u32 = !0u32;
+
+/// The handles `init` recorded
+struct Table {
+    /// Raw handle values, `NO_HANDLE` once taken
+    handles: [AtomicU32; MAX_STARTUP_HANDLES],
+
+    /// Info words of the handles
+    info: [u32; MAX_STARTUP_HANDLES],
+
+    /// Number of slots filled
+    count: usize,
+}
+
+/// Set once by `init`; empty until then
+static TABLE: OnceCell<Table> = OnceCell::new();
+
+/// Record the process's startup handles
+///
+/// `handles` and `info` are `count` handles and their info words. Handles
+/// past [`MAX_STARTUP_HANDLES`] are closed. The C runtime calls this once,
+/// before `main`; the handles of any later call are closed.
+///
+/// # Safety
+///
+/// `handles` and `info` must each point to `count` values, or be null
+/// with `count` 0, and the handles must be the process's to take.
+pub unsafe fn init(handles: *const u32, info: *const u32, count: usize) {
+    let mut table = Table {
+        handles: [const { AtomicU32::new(NO_HANDLE) }; MAX_STARTUP_HANDLES],
+        info: [0; MAX_STARTUP_HANDLES],
+        count: count.min(MAX_STARTUP_HANDLES),
+    };
+    for i in 0..table.count {
+        table.info[i] = *info.add(i);
+        *table.handles[i].get_mut() = *handles.add(i);
+    }
+    for i in table.count..count {
+        drop(Handle::from_raw(*handles.add(i), Rights::empty()));
+    }
+
+    if let Err(table) = TABLE.set(table) {
+        for raw in table.handles {
+            drop(Handle::from_raw(raw.into_inner(), Rights::empty()));
+        }
+    }
+}
+
+/// Take the first startup handle of type `kind` not yet taken
+///
+/// Returns the handle, which holds `rights`, and its info word.
+pub fn take(kind: u32, rights: Rights) -> Option<(Handle, u32)> {
+    let table = TABLE.get()?;
+    for i in 0..table.count {
+        let info = table.info[i];
+        if info & 0xFF != kind {
+            continue;
+        }
+        let raw = table.handles[i].swap(NO_HANDLE, Ordering::AcqRel);
+        if raw != NO_HANDLE {
+            return Some((unsafe { Handle::from_raw(raw, rights) }, info));
+        }
+    }
+    None
+}
+
+/// Take the root resource
+///
+/// # Errors
+///
+/// - `NotFound` - the process wasn't given the root resource, or it was
+///   already taken
+pub fn root_resource() -> Result<Handle> {
+    take(handle_type::RESOURCE, Rights::DUPLICATE | Rights::TRANSFER)
+        .map(|(handle, _)| handle)
+        .ok_or(Error::new(Status::NotFound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_each_handle_once() {
+        let handles = [10, 11, 12];
+        let info = [handle_type::NS_ROOT, handle_type::RESOURCE, handle_type::FD_VMO | 3 << 16];
+        assert!(take(handle_type::RESOURCE, Rights::DUPLICATE).is_none());
+        unsafe { init(handles.as_ptr(), info.as_ptr(), handles.len()) };
+
+        let (root, _) = take(handle_type::RESOURCE, Rights::DUPLICATE).unwrap();
+        assert_eq!(root.raw(), 11);
+        core::mem::forget(root);
+        assert!(take(handle_type::RESOURCE, Rights::DUPLICATE).is_none());
+
+        let (vmo, info) = take(handle_type::FD_VMO, Rights::READ).unwrap();
+        assert_eq!((vmo.raw(), info >> 16), (12, 3));
+        core::mem::forget(vmo);
+    }
+}
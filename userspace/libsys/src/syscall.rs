@@ -13,95 +13,14 @@
 
 /// System call numbers
 ///
-/// These must match the kernel's syscall definitions.
+/// Generated from `abi/syscalls.abi`, the table the kernel's syscall
+/// dispatch is generated from, so the two always agree. Syscalls marked
+/// reserved there have a number but the kernel returns `NotSupported`.
+#[rustux_macros::syscall_abi("../../abi/syscalls.abi")]
 #[repr(u64)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
-pub enum SyscallNumber {
-    // Process/thread syscalls
-    ProcessCreate = 0x01,
-    ProcessStart = 0x02,
-    ProcessExit = 0x03,
-    ThreadCreate = 0x04,
-    ThreadStart = 0x05,
-    ThreadExit = 0x06,
-    ThreadReadState = 0x07,
-    ThreadWriteState = 0x08,
-    ThreadResume = 0x09,
-
-    // Virtual memory syscalls
-    VmoCreate = 0x10,
-    VmoRead = 0x11,
-    VmoWrite = 0x12,
-    VmoGetSize = 0x13,
-    VmoSetSize = 0x14,
-    VmoOpRange = 0x15,
-    VmarMap = 0x16,
-    VmarUnmap = 0x17,
-    VmarProtect = 0x18,
-    VmarDestroy = 0x19,
-
-    // Handle syscalls
-    HandleClose = 0x20,
-    HandleDuplicate = 0x21,
-    HandleReplace = 0x22,
-    HandleCloseMany = 0x23,
-
-    // Object operations
-    ObjectSignal = 0x30,
-    ObjectWaitOne = 0x31,
-    ObjectWaitMany = 0x32,
-    ObjectGetProperty = 0x33,
-    ObjectSetProperty = 0x34,
-    ObjectSignalPeer = 0x35,
-    ObjectGetInfo = 0x36,
-
-    // Channel IPC
-    ChannelCreate = 0x40,
-    ChannelRead = 0x41,
-    ChannelWrite = 0x42,
-    ChannelCallEtc = 0x43,
-
-    // Event/EventPair
-    EventCreate = 0x50,
-    EventPairCreate = 0x51,
-
-    // Port/Waitset
-    PortCreate = 0x60,
-    PortQueue = 0x61,
-    PortWait = 0x62,
-    PortCancel = 0x63,
-
-    // Futex
-    FutexWait = 0x70,
-    FutexWake = 0x71,
-    FutexRequeue = 0x72,
-
-    // Policy
-    PolicyGetProfile = 0x80,
-    PolicySetProfile = 0x81,
-
-    // Hypervisor
-    HypervisorCreate = 0x90,
-    HypervisorOp = 0x91,
-
-    // Misc
-    SystemGetVersion = 0xA0,
-    SystemGetPhysMem = 0xA1,
-    SystemPowerctl = 0xA2,
-
-    // Bootstrap
-    ProcArgs = 0xB0,
-    VmarRootSelf = 0xB1,
-    JobDefault = 0xB2,
-    ThreadSelf = 0xB3,
-
-    // Socket
-    SocketCreate = 0xC0,
-    SocketWrite = 0xC1,
-    SocketRead = 0xC2,
-    SocketShutdown = 0xC3,
-}
+pub enum SyscallNumber {}
 
 /// Make a syscall with no arguments
 #[inline]
@@ -0,0 +1,41 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Rustux Filesystem Protocol (libvfs)
+//!
+//! The `Vfs` protocol every filesystem server speaks, so clients don't
+//! care which filesystem is behind a channel:
+//! - Paths are UTF-8, `/`-separated and relative to the server's root
+//! - Files and directories are opened by path and then named by the node
+//!   ID `open` returns, until `close`
+//! - Reads and writes carry their data in the message, up to
+//!   `MAX_TRANSFER` bytes each
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use vfs::{open_flags, VfsProxy};
+//!
+//! fn hello(channel: ipc::Channel) -> libsys::Result<()> {
+//!     let mut fs = VfsProxy::new(channel);
+//!     let flags = open_flags::WRITE | open_flags::CREATE | open_flags::TRUNCATE;
+//!     let node = fs.open(b"EFI/hello.txt".to_vec(), flags)?;
+//!     fs.write(node, 0, b"Hello, World!".to_vec())?;
+//!     fs.close(node)?;
+//!     fs.sync()
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod path;
+pub mod protocol;
+
+// Re-export commonly used types
+pub use protocol::{node_kind, open_flags, DirEntry, FsInfo, NodeAttr, Vfs, VfsProxy, VfsServer};
+pub use protocol::{MAX_DIR_BATCH, MAX_NAME, MAX_PATH, MAX_TRANSFER};
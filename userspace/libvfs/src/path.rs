@@ -0,0 +1,81 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Paths
+//!
+//! Servers resolve paths a component at a time. `components` splits a
+//! path and checks it: empty components and `.` are dropped, and `..`
+//! goes up a level, but never above the root. A leading `/` makes no
+//! difference.
+
+use alloc::vec::Vec;
+
+use libsys::{Error, Result, Status};
+
+use crate::protocol::{MAX_NAME, MAX_PATH};
+
+/// The components of `path`, with `.` and `..` resolved
+///
+/// Fails with `InvalidArgs` if the path is too long, isn't UTF-8, or
+/// has a NUL or an over-long name in it.
+pub fn components(path: &[u8]) -> Result<Vec<&str>> {
+    if path.len() > MAX_PATH || path.contains(&0) {
+        return Err(Error::new(Status::InvalidArgs));
+    }
+    let path = core::str::from_utf8(path).map_err(|_| Error::new(Status::InvalidArgs))?;
+
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            name if name.len() > MAX_NAME => return Err(Error::new(Status::InvalidArgs)),
+            name => parts.push(name),
+        }
+    }
+    Ok(parts)
+}
+
+/// The components of `path`'s parent directory, and its last name
+///
+/// Fails with `InvalidArgs` for the root, which has no name.
+pub fn split_last(path: &[u8]) -> Result<(Vec<&str>, &str)> {
+    let mut parts = components(path)?;
+    let name = parts.pop().ok_or(Error::new(Status::InvalidArgs))?;
+    Ok((parts, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_components() {
+        assert_eq!(
+            components(b"/EFI/BOOT/BOOTX64.EFI").unwrap(),
+            vec!["EFI", "BOOT", "BOOTX64.EFI"]
+        );
+        assert_eq!(components(b"a//./b/../c/").unwrap(), vec!["a", "c"]);
+        assert_eq!(components(b"../../a").unwrap(), vec!["a"]);
+        assert!(components(b"").unwrap().is_empty());
+        assert!(components(b"/").unwrap().is_empty());
+
+        assert!(components(b"a\0b").is_err());
+        assert!(components(&[b'a', 0xFF]).is_err());
+        assert!(components(&[b'a'; MAX_NAME + 1]).is_err());
+        assert!(components(&[b'a'; MAX_PATH + 1]).is_err());
+    }
+
+    #[test]
+    fn test_split_last() {
+        assert_eq!(split_last(b"EFI/BOOT").unwrap(), (vec!["EFI"], "BOOT"));
+        assert_eq!(split_last(b"/startup.nsh").unwrap(), (vec![], "startup.nsh"));
+        assert_eq!(split_last(b"/a/..").unwrap_err().status(), Status::InvalidArgs);
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! The Vfs Protocol
+//!
+//! A client holds one channel to a filesystem server and calls the
+//! methods of [`Vfs`] over it. `open` resolves a path to a node ID that
+//! stays valid until `close`; the other file methods take that ID. IDs
+//! belong to the channel they were opened on.
+//!
+//! # Errors
+//!
+//! Servers report failures with the usual statuses:
+//! - `NotFound` - a path component doesn't exist
+//! - `AlreadyExists` - `mkdir`, `rename` or an exclusive `open` found
+//!   something in the way
+//! - `WrongType` - a directory was used as a file or the other way round
+//! - `AccessDenied` - the node wasn't opened for the access asked for,
+//!   or is read-only
+//! - `BadState` - a directory being removed isn't empty
+//! - `Busy` - a node being removed or renamed is open
+//! - `InvalidArgs` - a bad path, name, node ID or transfer size
+//! - `NoMemory` - the filesystem is full
+
+use alloc::vec::Vec;
+
+use ipc::protocol::Dispatch;
+use ipc::Channel;
+use libsys::{Result, Status};
+use rustux_macros::{ipc_protocol, Wire};
+
+/// Most bytes one `read` or `write` moves
+pub const MAX_TRANSFER: u32 = 32 * 1024;
+
+/// Most entries one `read_dir` returns
+pub const MAX_DIR_BATCH: u32 = 64;
+
+/// Longest path, in bytes
+pub const MAX_PATH: usize = 1024;
+
+/// Longest name of a single node, in bytes
+pub const MAX_NAME: usize = 255;
+
+/// Flags of `open`
+pub mod open_flags {
+    /// Allow `read`
+    pub const READ: u32 = 1 << 0;
+
+    /// Allow `write` and `truncate`; files only
+    pub const WRITE: u32 = 1 << 1;
+
+    /// Create the file if it doesn't exist
+    pub const CREATE: u32 = 1 << 2;
+
+    /// With `CREATE`, fail if the file already exists
+    pub const EXCLUSIVE: u32 = 1 << 3;
+
+    /// Empty the file; needs `WRITE`
+    pub const TRUNCATE: u32 = 1 << 4;
+
+    /// Fail unless the node is a directory
+    pub const DIRECTORY: u32 = 1 << 5;
+}
+
+/// Kinds of node
+pub mod node_kind {
+    pub const FILE: u32 = 1;
+    pub const DIRECTORY: u32 = 2;
+}
+
+/// What `stat` reports about a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Wire)]
+pub struct NodeAttr {
+    /// One of `node_kind`
+    pub kind: u32,
+
+    /// Size in bytes; 0 for directories
+    pub size: u64,
+
+    /// Last modification, in seconds since the Unix epoch (0 if unknown)
+    pub modified: u64,
+
+    /// Writes are refused
+    pub read_only: bool,
+}
+
+/// One entry of a directory listing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Wire)]
+pub struct DirEntry {
+    /// Name, UTF-8
+    pub name: Vec<u8>,
+
+    /// One of `node_kind`
+    pub kind: u32,
+
+    /// Size in bytes; 0 for directories
+    pub size: u64,
+
+    /// Cookie that continues the listing after this entry
+    pub cookie: u64,
+}
+
+/// What `query` reports about a filesystem
+#[derive(Debug, Clone, Default, PartialEq, Eq, Wire)]
+pub struct FsInfo {
+    /// Filesystem type, such as `fat32`
+    pub name: Vec<u8>,
+
+    /// Volume label, UTF-8; empty if there is none
+    pub label: Vec<u8>,
+
+    /// Allocation unit in bytes
+    pub block_size: u32,
+
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+}
+
+/// Methods of a filesystem server
+#[ipc_protocol(version = 1)]
+pub trait Vfs {
+    /// Describe the filesystem
+    #[ordinal(1)]
+    fn query(&mut self) -> Result<FsInfo>;
+
+    /// Open the node at `path` with `open_flags`, returning its node ID
+    #[ordinal(2)]
+    fn open(&mut self, path: Vec<u8>, flags: u32) -> Result<u32>;
+
+    /// Forget a node ID
+    #[ordinal(3)]
+    fn close(&mut self, node: u32) -> Result<()>;
+
+    /// Describe an open node
+    #[ordinal(4)]
+    fn stat(&mut self, node: u32) -> Result<NodeAttr>;
+
+    /// Read up to `len` bytes at `offset`; fewer at the end of the file
+    #[ordinal(5)]
+    fn read(&mut self, node: u32, offset: u64, len: u32) -> Result<Vec<u8>>;
+
+    /// Write `data` at `offset`, returning the bytes written
+    ///
+    /// Writing past the end of the file fills the gap with zeros.
+    #[ordinal(6)]
+    fn write(&mut self, node: u32, offset: u64, data: Vec<u8>) -> Result<u32>;
+
+    /// Cut or extend a file to `size` bytes, extending with zeros
+    #[ordinal(7)]
+    fn truncate(&mut self, node: u32, size: u64) -> Result<()>;
+
+    /// List directory entries after `cookie`, which is 0 to start
+    ///
+    /// Returns at most `MAX_DIR_BATCH` entries, and none at the end of the
+    /// directory. `.` and `..` are not listed.
+    #[ordinal(8)]
+    fn read_dir(&mut self, node: u32, cookie: u64) -> Result<Vec<DirEntry>>;
+
+    /// Create a directory
+    #[ordinal(9)]
+    fn mkdir(&mut self, path: Vec<u8>) -> Result<()>;
+
+    /// Remove a file or an empty directory
+    #[ordinal(10)]
+    fn unlink(&mut self, path: Vec<u8>) -> Result<()>;
+
+    /// Move a node to a path that doesn't exist yet
+    #[ordinal(11)]
+    fn rename(&mut self, from: Vec<u8>, to: Vec<u8>) -> Result<()>;
+
+    /// Write everything cached to the device
+    #[ordinal(12)]
+    fn sync(&mut self) -> Result<()>;
+}
+
+/// Answer every request waiting on `channel`
+///
+/// Returns true if any request was answered. Fails with `PeerClosed` once
+/// the client has closed its end and nothing is left to answer.
+pub fn poll<D: Dispatch>(server: &mut D, channel: &Channel) -> Result<bool> {
+    let mut request = alloc::vec![0u8; ipc::protocol::MAX_MESSAGE_SIZE];
+    let mut handles = Vec::new();
+    let mut progress = false;
+    loop {
+        handles.clear();
+        match channel.read(&mut request, &mut handles) {
+            Ok(len) if len > 0 => {
+                ipc::protocol::serve(server, channel, &request[..len], &mut handles)?;
+                progress = true;
+            }
+            Err(e) if e.status() == Status::PeerClosed && !progress => return Err(e),
+            // Empty
+            _ => return Ok(progress),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipc::protocol::{Decoder, Encoder, Wire};
+
+    #[test]
+    fn test_wire_roundtrip() {
+        let entry = DirEntry {
+            name: b"BOOTX64.EFI".to_vec(),
+            kind: node_kind::FILE,
+            size: 1234,
+            cookie: 7,
+        };
+        let attr = NodeAttr {
+            kind: node_kind::DIRECTORY,
+            size: 0,
+            modified: 1_700_000_000,
+            read_only: true,
+        };
+        let mut enc = Encoder::new();
+        entry.clone().encode(&mut enc);
+        attr.encode(&mut enc);
+        assert_eq!(enc.bytes().len(), (4 + 11 + 4 + 8 + 8) + (4 + 8 + 8 + 1));
+
+        let mut dec = Decoder::new(enc.bytes(), &mut []);
+        assert_eq!(DirEntry::decode(&mut dec).unwrap(), entry);
+        assert_eq!(NodeAttr::decode(&mut dec).unwrap(), attr);
+        assert!(dec.finish().is_ok());
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ARP
+//!
+//! RFC 826 for Ethernet and IPv4 only. The cache is a short list with
+//! oldest-first eviction; entries never age out on their own.
+
+use alloc::collections::VecDeque;
+use core::net::Ipv4Addr;
+
+use crate::ethernet::MacAddr;
+
+/// Size of an Ethernet/IPv4 ARP packet
+pub const PACKET_SIZE: usize = 28;
+
+/// Number of cached translations
+pub const CACHE_SIZE: usize = 32;
+
+/// ARP operations
+pub mod op {
+    pub const REQUEST: u16 = 1;
+    pub const REPLY: u16 = 2;
+}
+
+/// Ethernet/IPv4 ARP packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpPacket {
+    pub op: u16,
+    pub sender_mac: MacAddr,
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: MacAddr,
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpPacket {
+    /// Parse a packet; `None` for other hardware or protocol types
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < PACKET_SIZE {
+            return None;
+        }
+        // htype 1 (Ethernet), ptype 0x0800, hlen 6, plen 4
+        if buf[0..6] != [0x00, 0x01, 0x08, 0x00, 6, 4] {
+            return None;
+        }
+        let mut sender_mac = [0u8; 6];
+        let mut target_mac = [0u8; 6];
+        sender_mac.copy_from_slice(&buf[8..14]);
+        target_mac.copy_from_slice(&buf[18..24]);
+        Some(Self {
+            op: u16::from_be_bytes([buf[6], buf[7]]),
+            sender_mac,
+            sender_ip: Ipv4Addr::new(buf[14], buf[15], buf[16], buf[17]),
+            target_mac,
+            target_ip: Ipv4Addr::new(buf[24], buf[25], buf[26], buf[27]),
+        })
+    }
+
+    /// Write the packet to the start of `buf`
+    pub fn write(&self, buf: &mut [u8]) -> usize {
+        buf[0..6].copy_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4]);
+        buf[6..8].copy_from_slice(&self.op.to_be_bytes());
+        buf[8..14].copy_from_slice(&self.sender_mac);
+        buf[14..18].copy_from_slice(&self.sender_ip.octets());
+        buf[18..24].copy_from_slice(&self.target_mac);
+        buf[24..28].copy_from_slice(&self.target_ip.octets());
+        PACKET_SIZE
+    }
+}
+
+/// IPv4 to MAC translations
+#[derive(Debug, Default)]
+pub struct ArpCache {
+    entries: VecDeque<(Ipv4Addr, MacAddr)>,
+}
+
+impl ArpCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up `ip`
+    pub fn lookup(&self, ip: Ipv4Addr) -> Option<MacAddr> {
+        self.entries.iter().find(|(addr, _)| *addr == ip).map(|(_, mac)| *mac)
+    }
+
+    /// Record or refresh a translation
+    pub fn insert(&mut self, ip: Ipv4Addr, mac: MacAddr) {
+        if let Some(entry) = self.entries.iter_mut().find(|(addr, _)| *addr == ip) {
+            entry.1 = mac;
+            return;
+        }
+        if self.entries.len() == CACHE_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((ip, mac));
+    }
+
+    /// Number of cached translations
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let packet = ArpPacket {
+            op: op::REQUEST,
+            sender_mac: [2, 0, 0, 0, 0, 1],
+            sender_ip: Ipv4Addr::new(10, 0, 2, 15),
+            target_mac: [0; 6],
+            target_ip: Ipv4Addr::new(10, 0, 2, 2),
+        };
+        let mut buf = [0u8; PACKET_SIZE];
+        packet.write(&mut buf);
+        assert_eq!(ArpPacket::parse(&buf), Some(packet));
+
+        // Wrong protocol type
+        buf[2] = 0x86;
+        assert_eq!(ArpPacket::parse(&buf), None);
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest() {
+        let mut cache = ArpCache::new();
+        for i in 0..=CACHE_SIZE as u8 {
+            cache.insert(Ipv4Addr::new(10, 0, 0, i), [i; 6]);
+        }
+        assert_eq!(cache.len(), CACHE_SIZE);
+        assert_eq!(cache.lookup(Ipv4Addr::new(10, 0, 0, 0)), None);
+        assert_eq!(cache.lookup(Ipv4Addr::new(10, 0, 0, 5)), Some([5; 6]));
+
+        cache.insert(Ipv4Addr::new(10, 0, 0, 5), [9; 6]);
+        assert_eq!(cache.lookup(Ipv4Addr::new(10, 0, 0, 5)), Some([9; 6]));
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Internet Checksum
+//!
+//! RFC 1071 ones' complement sum, shared by IPv4, ICMP, UDP and TCP.
+
+use core::net::Ipv4Addr;
+
+/// Running ones' complement sum
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Checksum {
+    sum: u32,
+}
+
+impl Checksum {
+    /// Start an empty sum
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `data`, padding an odd trailing byte with zero
+    pub fn add(&mut self, data: &[u8]) -> &mut Self {
+        let mut chunks = data.chunks_exact(2);
+        for pair in &mut chunks {
+            self.add_u16(u16::from_be_bytes([pair[0], pair[1]]));
+        }
+        if let [last] = chunks.remainder() {
+            self.add_u16(u16::from_be_bytes([*last, 0]));
+        }
+        self
+    }
+
+    /// Add one 16-bit word
+    pub fn add_u16(&mut self, value: u16) -> &mut Self {
+        self.sum += value as u32;
+        self.sum = (self.sum & 0xffff) + (self.sum >> 16);
+        self
+    }
+
+    /// Add the IPv4 pseudo-header used by UDP and TCP
+    pub fn add_pseudo_header(&mut self, src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, len: u16) -> &mut Self {
+        self.add(&src.octets()).add(&dst.octets()).add_u16(protocol as u16).add_u16(len)
+    }
+
+    /// Final checksum value
+    pub fn finish(&self) -> u16 {
+        !(self.sum as u16)
+    }
+}
+
+/// Checksum of `data`
+pub fn checksum(data: &[u8]) -> u16 {
+    Checksum::new().add(data).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc1071_example() {
+        // Example from RFC 1071 section 3
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(checksum(&data), !0xddf2);
+    }
+
+    #[test]
+    fn test_verifies_to_zero() {
+        let mut data = [0x45, 0x00, 0x00, 0x1c, 0x12, 0x34, 0x00, 0x00, 0x40, 0x01, 0, 0, 10, 0, 2, 15, 10, 0, 2, 2];
+        let sum = checksum(&data);
+        data[10..12].copy_from_slice(&sum.to_be_bytes());
+        assert_eq!(checksum(&data), 0);
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ethernet II Framing
+
+/// Hardware address
+pub type MacAddr = [u8; 6];
+
+/// All-stations address
+pub const BROADCAST: MacAddr = [0xff; 6];
+
+/// Size of the Ethernet II header
+pub const HEADER_SIZE: usize = 14;
+
+/// EtherType values
+pub mod ethertype {
+    pub const IPV4: u16 = 0x0800;
+    pub const ARP: u16 = 0x0806;
+}
+
+/// Ethernet II header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthHeader {
+    pub dst: MacAddr,
+    pub src: MacAddr,
+    pub ethertype: u16,
+}
+
+impl EthHeader {
+    /// Split a frame into its header and payload
+    pub fn parse(frame: &[u8]) -> Option<(Self, &[u8])> {
+        if frame.len() < HEADER_SIZE {
+            return None;
+        }
+        let mut dst = [0u8; 6];
+        let mut src = [0u8; 6];
+        dst.copy_from_slice(&frame[0..6]);
+        src.copy_from_slice(&frame[6..12]);
+        let header = Self {
+            dst,
+            src,
+            ethertype: u16::from_be_bytes([frame[12], frame[13]]),
+        };
+        Some((header, &frame[HEADER_SIZE..]))
+    }
+
+    /// Write the header to the start of `buf`
+    pub fn write(&self, buf: &mut [u8]) -> usize {
+        buf[0..6].copy_from_slice(&self.dst);
+        buf[6..12].copy_from_slice(&self.src);
+        buf[12..14].copy_from_slice(&self.ethertype.to_be_bytes());
+        HEADER_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = EthHeader {
+            dst: BROADCAST,
+            src: [0x52, 0x54, 0, 0x12, 0x34, 0x56],
+            ethertype: ethertype::ARP,
+        };
+        let mut frame = [0u8; 20];
+        assert_eq!(header.write(&mut frame), HEADER_SIZE);
+        let (parsed, payload) = EthHeader::parse(&frame).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(payload.len(), 6);
+        assert!(EthHeader::parse(&frame[..13]).is_none());
+    }
+}
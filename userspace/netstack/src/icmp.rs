@@ -0,0 +1,86 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ICMP Echo
+//!
+//! Only echo request and echo reply are understood; other messages are
+//! ignored.
+
+use crate::checksum::{checksum, Checksum};
+
+/// Size of the echo header
+pub const ECHO_HEADER_SIZE: usize = 8;
+
+/// Message types
+pub mod icmp_type {
+    pub const ECHO_REPLY: u8 = 0;
+    pub const ECHO_REQUEST: u8 = 8;
+}
+
+/// Echo request or reply header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Echo {
+    pub icmp_type: u8,
+    pub id: u16,
+    pub seq: u16,
+}
+
+impl Echo {
+    /// Parse an echo message, checking its checksum
+    pub fn parse(message: &[u8]) -> Option<(Self, &[u8])> {
+        if message.len() < ECHO_HEADER_SIZE || checksum(message) != 0 {
+            return None;
+        }
+        let icmp_type = message[0];
+        if message[1] != 0 || (icmp_type != icmp_type::ECHO_REQUEST && icmp_type != icmp_type::ECHO_REPLY) {
+            return None;
+        }
+        let echo = Self {
+            icmp_type,
+            id: u16::from_be_bytes([message[4], message[5]]),
+            seq: u16::from_be_bytes([message[6], message[7]]),
+        };
+        Some((echo, &message[ECHO_HEADER_SIZE..]))
+    }
+
+    /// Write the message with `payload` to `buf`, returning its size
+    pub fn write(&self, payload: &[u8], buf: &mut [u8]) -> usize {
+        let len = ECHO_HEADER_SIZE + payload.len();
+        buf[0] = self.icmp_type;
+        buf[1] = 0;
+        buf[2..4].fill(0);
+        buf[4..6].copy_from_slice(&self.id.to_be_bytes());
+        buf[6..8].copy_from_slice(&self.seq.to_be_bytes());
+        buf[ECHO_HEADER_SIZE..len].copy_from_slice(payload);
+        let sum = Checksum::new().add(&buf[..len]).finish();
+        buf[2..4].copy_from_slice(&sum.to_be_bytes());
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_roundtrip() {
+        let echo = Echo {
+            icmp_type: icmp_type::ECHO_REQUEST,
+            id: 0x4242,
+            seq: 3,
+        };
+        let mut buf = [0u8; 64];
+        let len = echo.write(b"abcde", &mut buf);
+        assert_eq!(len, 13);
+
+        let (parsed, payload) = Echo::parse(&buf[..len]).unwrap();
+        assert_eq!(parsed, echo);
+        assert_eq!(payload, b"abcde");
+
+        buf[9] ^= 0xff;
+        assert!(Echo::parse(&buf[..len]).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! IPv4
+//!
+//! Headers are always written without options; received options are
+//! skipped. Fragments are not reassembled and are dropped on receipt.
+
+use core::net::Ipv4Addr;
+
+use crate::checksum::checksum;
+
+/// Size of a header without options
+pub const HEADER_SIZE: usize = 20;
+
+/// Default time to live for sent packets
+pub const DEFAULT_TTL: u8 = 64;
+
+/// IP protocol numbers
+pub mod protocol {
+    pub const ICMP: u8 = 1;
+    pub const TCP: u8 = 6;
+    pub const UDP: u8 = 17;
+}
+
+/// Don't Fragment flag in the flags/fragment offset field
+const FLAG_DF: u16 = 0x4000;
+
+/// More Fragments flag and fragment offset bits
+const FRAGMENT_MASK: u16 = 0x3fff;
+
+/// IPv4 header fields the stack uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Header {
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub protocol: u8,
+    pub ttl: u8,
+    pub id: u16,
+
+    /// Payload length in bytes
+    pub payload_len: u16,
+}
+
+impl Ipv4Header {
+    /// Validate a packet and split it into header and payload
+    ///
+    /// Rejects bad versions, lengths and checksums, and fragments.
+    pub fn parse(packet: &[u8]) -> Option<(Self, &[u8])> {
+        if packet.len() < HEADER_SIZE || packet[0] >> 4 != 4 {
+            return None;
+        }
+        let header_len = (packet[0] & 0xf) as usize * 4;
+        let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        if header_len < HEADER_SIZE || total_len < header_len || total_len > packet.len() {
+            return None;
+        }
+        if checksum(&packet[..header_len]) != 0 {
+            return None;
+        }
+        if u16::from_be_bytes([packet[6], packet[7]]) & FRAGMENT_MASK != 0 {
+            return None;
+        }
+
+        let header = Self {
+            src: Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]),
+            dst: Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]),
+            protocol: packet[9],
+            ttl: packet[8],
+            id: u16::from_be_bytes([packet[4], packet[5]]),
+            payload_len: (total_len - header_len) as u16,
+        };
+        // Ethernet pads short frames; trim to the real length
+        Some((header, &packet[header_len..total_len]))
+    }
+
+    /// Write the header to the start of `buf`
+    pub fn write(&self, buf: &mut [u8]) -> usize {
+        let total_len = HEADER_SIZE as u16 + self.payload_len;
+        buf[0] = 0x45;
+        buf[1] = 0;
+        buf[2..4].copy_from_slice(&total_len.to_be_bytes());
+        buf[4..6].copy_from_slice(&self.id.to_be_bytes());
+        buf[6..8].copy_from_slice(&FLAG_DF.to_be_bytes());
+        buf[8] = self.ttl;
+        buf[9] = self.protocol;
+        buf[10..12].fill(0);
+        buf[12..16].copy_from_slice(&self.src.octets());
+        buf[16..20].copy_from_slice(&self.dst.octets());
+        let sum = checksum(&buf[..HEADER_SIZE]);
+        buf[10..12].copy_from_slice(&sum.to_be_bytes());
+        HEADER_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Ipv4Header {
+        Ipv4Header {
+            src: Ipv4Addr::new(10, 0, 2, 15),
+            dst: Ipv4Addr::new(10, 0, 2, 2),
+            protocol: protocol::UDP,
+            ttl: DEFAULT_TTL,
+            id: 0x1234,
+            payload_len: 8,
+        }
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let mut packet = [0u8; 46];
+        header().write(&mut packet);
+
+        // Trailing ethernet padding is not payload
+        let (parsed, payload) = Ipv4Header::parse(&packet).unwrap();
+        assert_eq!(parsed, header());
+        assert_eq!(payload.len(), 8);
+    }
+
+    #[test]
+    fn test_rejects_bad_packets() {
+        let mut packet = [0u8; 28];
+        header().write(&mut packet);
+
+        let mut corrupt = packet;
+        corrupt[15] ^= 1;
+        assert!(Ipv4Header::parse(&corrupt).is_none());
+
+        assert!(Ipv4Header::parse(&packet[..27]).is_none());
+
+        // More Fragments set (checksum fixed up)
+        let mut fragment = packet;
+        fragment[6] = 0x20;
+        fragment[10..12].fill(0);
+        let sum = checksum(&fragment[..HEADER_SIZE]);
+        fragment[10..12].copy_from_slice(&sum.to_be_bytes());
+        assert!(Ipv4Header::parse(&fragment).is_none());
+    }
+}
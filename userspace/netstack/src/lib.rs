@@ -0,0 +1,58 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Rustux Network Stack
+//!
+//! A small IPv4 stack running on top of any DDK `EthernetDevice`:
+//! - Ethernet II framing
+//! - ARP resolution and replies
+//! - IPv4 without fragmentation or options
+//! - ICMP echo (answering pings and sending them)
+//! - UDP with a channel-based socket API
+//! - TCP with retransmission and flow control, over the same socket
+//!   protocol
+//!
+//! The stack is polled: `Stack::poll` moves frames between the link and
+//! the protocol layers and between bound sockets and the wire.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use core::net::{Ipv4Addr, SocketAddrV4};
+//! use netstack::{Config, Stack};
+//!
+//! fn echo(link: impl libddk::EthernetDevice) -> libsys::Result<()> {
+//!     let mut stack = Stack::new(link, Config::qemu_user());
+//!     let socket = stack.udp_bind(7)?;
+//!     let mut buf = [0u8; 1500];
+//!     loop {
+//!         stack.poll()?;
+//!         if let Some((len, from)) = socket.recvfrom(&mut buf)? {
+//!             socket.sendto(from, &buf[..len])?;
+//!         }
+//!     }
+//! }
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod arp;
+pub mod checksum;
+pub mod ethernet;
+pub mod icmp;
+pub mod ipv4;
+pub mod socket;
+pub mod stack;
+pub mod tcb;
+pub mod tcp;
+pub mod udp;
+
+// Re-export commonly used types
+pub use ethernet::MacAddr;
+pub use socket::{TcpListener, TcpStream, UdpSocket};
+pub use stack::{Config, EchoReply, Stack};
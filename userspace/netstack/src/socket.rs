@@ -0,0 +1,334 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Sockets
+//!
+//! A socket is one end of a channel whose other end the stack keeps.
+//! Every message starts with the same header:
+//!
+//! ```text
+//! +0  addr     [u8; 4]   Remote IPv4 address
+//! +4  port     u16       Remote port (big-endian)
+//! +6  op       u8        Message kind (`op`)
+//! +7  reserved u8
+//! +8  payload
+//! ```
+//!
+//! UDP sockets only exchange `DATA` messages, one per datagram: those
+//! sent name the destination, those received the source.
+//!
+//! A TCP stream exchanges `DATA` messages carrying stream bytes (the
+//! address is unused), and `CONNECTED`, `SHUTDOWN` and `CLOSE` to track
+//! the connection; see `op`. A TCP listener receives one `ACCEPTED`
+//! message per connection, carrying the connection's stream channel.
+//! Nothing here is Rust-specific, so a C library can speak the same
+//! protocol to offer `connect()` and `accept()`.
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use libipc::Channel;
+use libsys::{Error, Handle, Result, Status};
+
+/// Size of the address prefix
+pub const ADDR_HEADER_SIZE: usize = 8;
+
+/// Largest payload that fits an unfragmented packet on a 1500-byte MTU
+pub const MAX_DATAGRAM: usize = 1472;
+
+/// Largest socket message
+pub const MAX_MESSAGE_SIZE: usize = ADDR_HEADER_SIZE + MAX_DATAGRAM;
+
+/// Stream bytes a `TcpStream` buffers before leaving the rest queued
+const STREAM_BUFFER_SIZE: usize = 16384;
+
+/// Message kinds
+pub mod op {
+    /// A UDP datagram, or TCP stream bytes
+    pub const DATA: u8 = 0;
+
+    /// To a stream: the handshake completed; the address is the peer
+    pub const CONNECTED: u8 = 1;
+
+    /// To a listener: a new connection, whose stream channel is attached;
+    /// the address is the peer
+    pub const ACCEPTED: u8 = 2;
+
+    /// From a stream: no more data will be sent. To a stream: the peer
+    /// has finished sending
+    pub const SHUTDOWN: u8 = 3;
+
+    /// From a socket: release it. To a stream: the connection is gone;
+    /// the payload is the status (i32, little-endian), 0 if it ended in
+    /// an orderly close
+    pub const CLOSE: u8 = 4;
+}
+
+/// Prefix `payload` with `addr` and `op` into `buf`
+pub(crate) fn encode(addr: SocketAddrV4, op: u8, payload: &[u8], buf: &mut [u8]) -> usize {
+    buf[0..4].copy_from_slice(&addr.ip().octets());
+    buf[4..6].copy_from_slice(&addr.port().to_be_bytes());
+    buf[6] = op;
+    buf[7] = 0;
+    buf[ADDR_HEADER_SIZE..ADDR_HEADER_SIZE + payload.len()].copy_from_slice(payload);
+    ADDR_HEADER_SIZE + payload.len()
+}
+
+/// Split a socket message into address, op and payload
+pub(crate) fn decode(message: &[u8]) -> Option<(SocketAddrV4, u8, &[u8])> {
+    if message.len() < ADDR_HEADER_SIZE {
+        return None;
+    }
+    let ip = Ipv4Addr::new(message[0], message[1], message[2], message[3]);
+    let port = u16::from_be_bytes([message[4], message[5]]);
+    Some((SocketAddrV4::new(ip, port), message[6], &message[ADDR_HEADER_SIZE..]))
+}
+
+/// Send a message without payload
+pub(crate) fn send_control(channel: &Channel, addr: SocketAddrV4, op: u8, handles: &[Handle]) -> Result<()> {
+    let mut buf = [0u8; ADDR_HEADER_SIZE];
+    encode(addr, op, &[], &mut buf);
+    channel.write(&buf, handles)
+}
+
+/// Address used where the protocol ignores it
+pub(crate) const UNSPECIFIED: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+
+/// A bound UDP socket
+pub struct UdpSocket {
+    channel: Channel,
+    local_port: u16,
+}
+
+impl UdpSocket {
+    pub(crate) fn new(channel: Channel, local_port: u16) -> Self {
+        Self { channel, local_port }
+    }
+
+    /// Port the socket is bound to
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// Queue `data` for `to`
+    ///
+    /// The datagram leaves on a later `Stack::poll`.
+    pub fn sendto(&self, to: SocketAddrV4, data: &[u8]) -> Result<()> {
+        if data.len() > MAX_DATAGRAM {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+        let mut buf = vec![0u8; ADDR_HEADER_SIZE + data.len()];
+        encode(to, op::DATA, data, &mut buf);
+        self.channel.write(&buf, &[])
+    }
+
+    /// Take the next received datagram
+    ///
+    /// Returns its length and source, or `None` if nothing is queued.
+    /// Datagrams longer than `buf` are truncated.
+    pub fn recvfrom(&self, buf: &mut [u8]) -> Result<Option<(usize, SocketAddrV4)>> {
+        let mut message = [0u8; MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+        let len = match self.channel.read(&mut message, &mut handles) {
+            // Every message has an address, so zero bytes means none queued
+            Ok(0) => return Ok(None),
+            Ok(len) => len,
+            Err(err) if err.status() == Status::WouldBlock => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let (from, _, payload) = decode(&message[..len]).ok_or(Error::new(Status::Internal))?;
+        let n = payload.len().min(buf.len());
+        buf[..n].copy_from_slice(&payload[..n]);
+        Ok(Some((n, from)))
+    }
+
+    /// Release the port
+    pub fn close(self) -> Result<()> {
+        send_control(&self.channel, UNSPECIFIED, op::CLOSE, &[])?;
+        self.channel.into_handle().close()
+    }
+}
+
+/// One end of a TCP connection
+pub struct TcpStream {
+    channel: Channel,
+    local_port: u16,
+    peer: Option<SocketAddrV4>,
+    rx: VecDeque<u8>,
+    eof: bool,
+    closed: Option<Status>,
+}
+
+impl TcpStream {
+    pub(crate) fn new(channel: Channel, local_port: u16, peer: Option<SocketAddrV4>) -> Self {
+        Self {
+            channel,
+            local_port,
+            peer,
+            rx: VecDeque::new(),
+            eof: false,
+            closed: None,
+        }
+    }
+
+    /// Port the connection uses locally
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// The peer, once connected
+    pub fn peer_addr(&self) -> Option<SocketAddrV4> {
+        self.peer
+    }
+
+    /// Whether the handshake has completed
+    ///
+    /// Fails with the reason if the connection attempt failed.
+    pub fn is_connected(&mut self) -> Result<bool> {
+        self.pump()?;
+        match self.closed {
+            Some(Status::Ok) | None => Ok(self.peer.is_some()),
+            Some(status) => Err(Error::new(status)),
+        }
+    }
+
+    /// Queue `data` for sending
+    ///
+    /// Data may be queued before the connection completes. It leaves on
+    /// later `Stack::poll` calls as the peer's window allows.
+    pub fn send(&self, data: &[u8]) -> Result<()> {
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+        for chunk in data.chunks(MAX_DATAGRAM) {
+            let len = encode(UNSPECIFIED, op::DATA, chunk, &mut buf);
+            self.channel.write(&buf[..len], &[])?;
+        }
+        Ok(())
+    }
+
+    /// Read received data
+    ///
+    /// Returns `Some(0)` once the peer has finished sending and all data
+    /// was read, and `None` if nothing is available yet.
+    pub fn recv(&mut self, buf: &mut [u8]) -> Result<Option<usize>> {
+        self.pump()?;
+        if !self.rx.is_empty() {
+            let n = buf.len().min(self.rx.len());
+            for (dst, src) in buf.iter_mut().zip(self.rx.drain(..n)) {
+                *dst = src;
+            }
+            return Ok(Some(n));
+        }
+        match self.closed {
+            Some(Status::Ok) => Ok(Some(0)),
+            Some(status) => Err(Error::new(status)),
+            None if self.eof => Ok(Some(0)),
+            None => Ok(None),
+        }
+    }
+
+    /// Finish sending; the peer reads end-of-stream after queued data
+    pub fn shutdown(&self) -> Result<()> {
+        send_control(&self.channel, UNSPECIFIED, op::SHUTDOWN, &[])
+    }
+
+    /// Close the connection
+    ///
+    /// Queued data is still delivered before the stack closes its side.
+    pub fn close(self) -> Result<()> {
+        send_control(&self.channel, UNSPECIFIED, op::CLOSE, &[])?;
+        self.channel.into_handle().close()
+    }
+
+    /// Take pending messages from the stack
+    fn pump(&mut self) -> Result<()> {
+        let mut message = [0u8; MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+        while self.closed.is_none() && self.rx.len() < STREAM_BUFFER_SIZE {
+            let len = match self.channel.read(&mut message, &mut handles) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.status() == Status::WouldBlock => break,
+                Err(err) => return Err(err),
+            };
+            let (addr, op, payload) = match decode(&message[..len]) {
+                Some(decoded) => decoded,
+                None => continue,
+            };
+            match op {
+                op::DATA => self.rx.extend(payload),
+                op::CONNECTED => self.peer = Some(addr),
+                op::SHUTDOWN => self.eof = true,
+                op::CLOSE => {
+                    let raw = match payload {
+                        [a, b, c, d, ..] => i32::from_le_bytes([*a, *b, *c, *d]),
+                        _ => 0,
+                    };
+                    self.closed = Some(Status::from_raw(raw));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A TCP port accepting connections
+pub struct TcpListener {
+    channel: Channel,
+    local_port: u16,
+}
+
+impl TcpListener {
+    pub(crate) fn new(channel: Channel, local_port: u16) -> Self {
+        Self { channel, local_port }
+    }
+
+    /// Port the listener is bound to
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// Take the next established connection, if any
+    pub fn accept(&self) -> Result<Option<TcpStream>> {
+        let mut message = [0u8; MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+        loop {
+            let len = match self.channel.read(&mut message, &mut handles) {
+                Ok(0) => return Ok(None),
+                Ok(len) => len,
+                Err(err) if err.status() == Status::WouldBlock => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            if let (Some((peer, op::ACCEPTED, _)), Some(handle)) = (decode(&message[..len]), handles.pop()) {
+                let channel = unsafe { Channel::from_handle(handle) };
+                return Ok(Some(TcpStream::new(channel, self.local_port, Some(peer))));
+            }
+        }
+    }
+
+    /// Stop listening; connections not yet accepted are reset
+    pub fn close(self) -> Result<()> {
+        send_control(&self.channel, UNSPECIFIED, op::CLOSE, &[])?;
+        self.channel.into_handle().close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_roundtrip() {
+        let addr = SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 2), 5555);
+        let mut buf = [0u8; 32];
+        let len = encode(addr, op::DATA, b"hello", &mut buf);
+        assert_eq!(len, ADDR_HEADER_SIZE + 5);
+        assert_eq!(decode(&buf[..len]), Some((addr, op::DATA, &b"hello"[..])));
+        assert_eq!(decode(&buf[..7]), None);
+    }
+}
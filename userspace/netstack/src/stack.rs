@@ -0,0 +1,999 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Protocol Stack
+//!
+//! `Stack` owns a link and a static address configuration. Each `poll`
+//! handles received frames, then sends what bound sockets have queued.
+//!
+//! Outgoing packets whose next hop has no ARP entry wait in a short
+//! pending queue while a request goes out; the ARP reply releases them.
+//!
+//! TCP timers run on the stack's clock. `poll_at` takes the time in
+//! milliseconds from any monotonic source; `poll` advances the clock by
+//! one millisecond per call, a stand-in for callers without a clock.
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use libddk::EthernetDevice;
+use libipc::Channel;
+use libsys::{Error, Result, Status};
+
+use crate::arp::{self, ArpCache, ArpPacket};
+use crate::ethernet::{self, ethertype, EthHeader, MacAddr, BROADCAST};
+use crate::icmp::{icmp_type, Echo};
+use crate::ipv4::{self, protocol, Ipv4Header};
+use crate::socket::{self, op, TcpListener, TcpStream, UdpSocket};
+use crate::tcb::{Segment, Tcb};
+use crate::tcp::{self, flags, TcpHeader};
+use crate::udp::{self, UdpHeader};
+
+/// Shortest frame on the wire, excluding the FCS
+const MIN_FRAME_SIZE: usize = 60;
+
+/// Largest frame the stack receives
+const MAX_FRAME_SIZE: usize = 1518;
+
+/// Packets held while waiting for ARP
+const MAX_PENDING: usize = 16;
+
+/// Echo replies kept for `take_echo_reply`
+const MAX_ECHO_REPLIES: usize = 16;
+
+/// Frames handled per `poll`
+const MAX_FRAMES_PER_POLL: usize = 32;
+
+/// First ephemeral port
+const EPHEMERAL_START: u16 = 49152;
+
+/// Open TCP connections, including those not yet accepted
+const MAX_CONNECTIONS: usize = 64;
+
+/// Static interface configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub ip: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+}
+
+impl Config {
+    /// Addresses QEMU user networking (slirp) assigns by default
+    pub const fn qemu_user() -> Self {
+        Self {
+            ip: Ipv4Addr::new(10, 0, 2, 15),
+            netmask: Ipv4Addr::new(255, 255, 255, 0),
+            gateway: Ipv4Addr::new(10, 0, 2, 2),
+        }
+    }
+
+    /// Whether `addr` is on the local subnet
+    pub fn is_local(&self, addr: Ipv4Addr) -> bool {
+        let mask = u32::from(self.netmask);
+        u32::from(addr) & mask == u32::from(self.ip) & mask
+    }
+
+    /// Directed broadcast address of the local subnet
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.ip) | !u32::from(self.netmask))
+    }
+}
+
+/// A received ICMP echo reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoReply {
+    pub src: Ipv4Addr,
+    pub id: u16,
+    pub seq: u16,
+
+    /// Payload length
+    pub len: usize,
+}
+
+/// A socket's port and the stack's end of its channel
+struct Binding {
+    port: u16,
+    channel: Channel,
+}
+
+/// An IPv4 packet waiting for its next hop to resolve
+struct Pending {
+    next_hop: Ipv4Addr,
+    packet: Vec<u8>,
+}
+
+/// A TCP listener's port and the stack's end of its channel
+struct Listener {
+    port: u16,
+    channel: Channel,
+    backlog: usize,
+}
+
+/// A TCP connection and its application channel
+struct Connection {
+    tcb: Tcb,
+
+    /// Stack end of the stream channel, once the application has one
+    channel: Option<Channel>,
+
+    /// Listening port, until the connection is handed to the listener
+    listener: Option<u16>,
+
+    /// Stream bytes taken from the channel that the TCB has no room for yet
+    tx_pending: Vec<u8>,
+
+    shut_down: bool,
+    app_closed: bool,
+    connected_sent: bool,
+    eof_sent: bool,
+    close_sent: bool,
+}
+
+impl Connection {
+    fn new(tcb: Tcb, channel: Option<Channel>, listener: Option<u16>) -> Self {
+        Self {
+            tcb,
+            channel,
+            listener,
+            tx_pending: Vec::new(),
+            shut_down: false,
+            app_closed: false,
+            connected_sent: false,
+            eof_sent: false,
+            close_sent: false,
+        }
+    }
+
+    /// Whether the connection can be dropped
+    fn finished(&self) -> bool {
+        self.tcb.is_closed() && (self.app_closed || self.channel.is_none())
+    }
+
+    /// Move data between the application and the TCB, and run its timers
+    fn service(&mut self, listeners: &[Listener], now: u64, out: &mut Vec<Segment>) -> bool {
+        let mut progress = false;
+        if let Some(port) = self.listener {
+            progress |= self.hand_over(port, listeners, out);
+        }
+        // Held out of `self` while the helpers borrow it mutably
+        let channel = self.channel.take();
+        if let (Some(channel), false) = (&channel, self.app_closed) {
+            progress |= self.take_from_app(channel);
+        }
+        self.tcb.poll(now, out);
+        match &channel {
+            Some(channel) if !self.app_closed => progress |= self.give_to_app(channel),
+            _ if self.app_closed => {
+                // Nobody will read it
+                let n = self.tcb.readable();
+                self.tcb.consume(n);
+            }
+            _ => {}
+        }
+        self.channel = channel;
+        progress
+    }
+
+    /// Pass an established passive connection to its listener
+    fn hand_over(&mut self, port: u16, listeners: &[Listener], out: &mut Vec<Segment>) -> bool {
+        if !self.tcb.is_synchronized() {
+            return false;
+        }
+        let listener = match listeners.iter().find(|l| l.port == port) {
+            Some(listener) => listener,
+            None => {
+                self.tcb.abort(out);
+                self.listener = None;
+                return true;
+            }
+        };
+        let (ours, theirs) = match Channel::create() {
+            Ok(pair) => pair,
+            Err(_) => return false,
+        };
+        let peer = self.tcb.remote();
+        if socket::send_control(&listener.channel, peer, op::ACCEPTED, core::slice::from_ref(theirs.handle())).is_err() {
+            // Try again on a later poll
+            return false;
+        }
+        // The kernel moved it to the listener
+        core::mem::forget(theirs);
+        self.channel = Some(ours);
+        self.listener = None;
+        self.connected_sent = true;
+        true
+    }
+
+    /// Feed stream messages from the application into the TCB
+    fn take_from_app(&mut self, channel: &Channel) -> bool {
+        let mut progress = false;
+        let mut message = [0u8; socket::MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+        loop {
+            if self.tcb.is_closed() {
+                self.tx_pending.clear();
+            }
+            if !self.tx_pending.is_empty() {
+                let n = self.tcb.send(&self.tx_pending);
+                self.tx_pending.drain(..n);
+                progress |= n > 0;
+                if !self.tx_pending.is_empty() {
+                    break;
+                }
+            }
+
+            let len = match channel.read(&mut message, &mut handles) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.status() == Status::WouldBlock => break,
+                // The application is gone
+                Err(_) => {
+                    self.tcb.shutdown();
+                    self.app_closed = true;
+                    break;
+                }
+            };
+            progress = true;
+            match socket::decode(&message[..len]) {
+                Some((_, op::DATA, payload)) if !self.shut_down => self.tx_pending.extend_from_slice(payload),
+                Some((_, op::SHUTDOWN, _)) => {
+                    self.tcb.shutdown();
+                    self.shut_down = true;
+                }
+                Some((_, op::CLOSE, _)) => {
+                    self.tcb.shutdown();
+                    self.app_closed = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        progress
+    }
+
+    /// Deliver received data and connection events to the application
+    fn give_to_app(&mut self, channel: &Channel) -> bool {
+        let mut progress = false;
+        if !self.connected_sent && self.tcb.is_synchronized() {
+            if socket::send_control(channel, self.tcb.remote(), op::CONNECTED, &[]).is_err() {
+                return false;
+            }
+            self.connected_sent = true;
+            progress = true;
+        }
+
+        let mut chunk = [0u8; socket::MAX_DATAGRAM];
+        let mut message = [0u8; socket::MAX_MESSAGE_SIZE];
+        while self.tcb.readable() > 0 {
+            let n = self.tcb.peek(&mut chunk);
+            let len = socket::encode(socket::UNSPECIFIED, op::DATA, &chunk[..n], &mut message);
+            // A full channel leaves the data here, closing the window
+            if channel.write(&message[..len], &[]).is_err() {
+                return progress;
+            }
+            self.tcb.consume(n);
+            progress = true;
+        }
+
+        if self.tcb.at_eof() && !self.eof_sent && socket::send_control(channel, socket::UNSPECIFIED, op::SHUTDOWN, &[]).is_ok() {
+            self.eof_sent = true;
+            progress = true;
+        }
+
+        if self.tcb.is_closed() && !self.close_sent {
+            let status = self.tcb.error().map_or(0, |status| status.into_raw());
+            let len = socket::encode(socket::UNSPECIFIED, op::CLOSE, &status.to_le_bytes(), &mut message);
+            if channel.write(&message[..len], &[]).is_ok() {
+                self.close_sent = true;
+                progress = true;
+            }
+        }
+        progress
+    }
+}
+
+/// IPv4 stack on one ethernet link
+pub struct Stack<L: EthernetDevice> {
+    link: L,
+    mac: MacAddr,
+    config: Config,
+    arp: ArpCache,
+    pending: VecDeque<Pending>,
+    sockets: Vec<Binding>,
+    listeners: Vec<Listener>,
+    connections: Vec<Connection>,
+    echo_replies: VecDeque<EchoReply>,
+    next_ip_id: u16,
+    next_port: u16,
+    next_iss: u32,
+
+    /// Milliseconds, as last given to `poll_at`
+    now: u64,
+}
+
+impl<L: EthernetDevice> Stack<L> {
+    /// Create a stack using `link` with `config`
+    pub fn new(link: L, config: Config) -> Self {
+        let mac = link.info().mac;
+        Self {
+            link,
+            mac,
+            config,
+            arp: ArpCache::new(),
+            pending: VecDeque::new(),
+            sockets: Vec::new(),
+            listeners: Vec::new(),
+            connections: Vec::new(),
+            echo_replies: VecDeque::new(),
+            next_ip_id: 1,
+            next_port: EPHEMERAL_START,
+            next_iss: u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]),
+            now: 0,
+        }
+    }
+
+    /// Interface configuration
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Interface MAC address
+    pub fn mac(&self) -> MacAddr {
+        self.mac
+    }
+
+    /// The underlying link
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// ============================================================================
+    /// Socket API
+    /// ============================================================================
+
+    /// Bind a UDP socket to `port`, or to a free ephemeral port if 0
+    pub fn udp_bind(&mut self, port: u16) -> Result<UdpSocket> {
+        let port = match port {
+            0 => self.ephemeral_port()?,
+            p if self.sockets.iter().any(|b| b.port == p) => {
+                return Err(Error::new(Status::AlreadyExists));
+            }
+            p => p,
+        };
+        let (ours, theirs) = Channel::create()?;
+        self.sockets.push(Binding { port, channel: ours });
+        Ok(UdpSocket::new(theirs, port))
+    }
+
+    /// Accept TCP connections on `port`, or on a free ephemeral port if 0
+    ///
+    /// Up to `backlog` connections may wait to be accepted; further SYNs
+    /// are dropped until one is.
+    pub fn tcp_listen(&mut self, port: u16, backlog: usize) -> Result<TcpListener> {
+        let port = match port {
+            0 => self.ephemeral_port()?,
+            p if self.listeners.iter().any(|l| l.port == p) => {
+                return Err(Error::new(Status::AlreadyExists));
+            }
+            p => p,
+        };
+        let (ours, theirs) = Channel::create()?;
+        self.listeners.push(Listener {
+            port,
+            channel: ours,
+            backlog: backlog.max(1),
+        });
+        Ok(TcpListener::new(theirs, port))
+    }
+
+    /// Open a TCP connection to `remote`
+    ///
+    /// Returns as soon as the SYN is queued; the stream reports when the
+    /// handshake completes.
+    pub fn tcp_connect(&mut self, remote: SocketAddrV4) -> Result<TcpStream> {
+        if self.connections.len() >= MAX_CONNECTIONS {
+            return Err(Error::new(Status::NoMemory));
+        }
+        let port = self.ephemeral_port()?;
+        let (ours, theirs) = Channel::create()?;
+        let local = SocketAddrV4::new(self.config.ip, port);
+        let iss = self.next_iss();
+        let mut out = Vec::new();
+        let tcb = Tcb::connect(local, remote, iss, self.mss(), self.now, &mut out);
+        self.connections.push(Connection::new(tcb, Some(ours), None));
+        self.send_segments(local, remote, out)?;
+        Ok(TcpStream::new(theirs, port, None))
+    }
+
+    fn ephemeral_port(&mut self) -> Result<u16> {
+        for _ in EPHEMERAL_START..=u16::MAX {
+            let port = self.next_port;
+            self.next_port = if port == u16::MAX { EPHEMERAL_START } else { port + 1 };
+            if !self.port_in_use(port) {
+                return Ok(port);
+            }
+        }
+        Err(Error::new(Status::NoMemory))
+    }
+
+    fn port_in_use(&self, port: u16) -> bool {
+        self.sockets.iter().any(|b| b.port == port)
+            || self.listeners.iter().any(|l| l.port == port)
+            || self.connections.iter().any(|c| c.tcb.local().port() == port)
+    }
+
+    /// Initial sequence number for a new connection
+    ///
+    /// RFC 793's clock-driven ISN with a per-connection step, so that
+    /// back-to-back connections do not overlap in sequence space.
+    fn next_iss(&mut self) -> u32 {
+        self.next_iss = self.next_iss.wrapping_add(0x9e37_79b9);
+        self.next_iss.wrapping_add((self.now as u32).wrapping_mul(250))
+    }
+
+    /// MSS for segments sent on this link
+    fn mss(&self) -> u16 {
+        self.link.info().mtu.saturating_sub((ipv4::HEADER_SIZE + tcp::HEADER_SIZE) as u16)
+    }
+
+    /// Send an ICMP echo request to `dst`
+    pub fn ping(&mut self, dst: Ipv4Addr, id: u16, seq: u16, payload: &[u8]) -> Result<()> {
+        let echo = Echo {
+            icmp_type: icmp_type::ECHO_REQUEST,
+            id,
+            seq,
+        };
+        let mut message = vec![0u8; crate::icmp::ECHO_HEADER_SIZE + payload.len()];
+        echo.write(payload, &mut message);
+        self.send_ip(dst, protocol::ICMP, &message)
+    }
+
+    /// Take the oldest echo reply carrying `id`
+    pub fn take_echo_reply(&mut self, id: u16) -> Option<EchoReply> {
+        let pos = self.echo_replies.iter().position(|reply| reply.id == id)?;
+        self.echo_replies.remove(pos)
+    }
+
+    /// ============================================================================
+    /// Polling
+    /// ============================================================================
+
+    /// Poll, counting each call as one millisecond
+    pub fn poll(&mut self) -> Result<bool> {
+        self.poll_at(self.now + 1)
+    }
+
+    /// Handle received frames, queued socket sends and timers at `now`
+    ///
+    /// `now` is in milliseconds and must not go backwards. Returns true
+    /// if anything was received or sent.
+    pub fn poll_at(&mut self, now: u64) -> Result<bool> {
+        self.now = self.now.max(now);
+        let mut progress = false;
+
+        let mut frame = [0u8; MAX_FRAME_SIZE];
+        for _ in 0..MAX_FRAMES_PER_POLL {
+            let len = self.link.recv(&mut frame)?;
+            if len == 0 {
+                break;
+            }
+            self.handle_frame(&frame[..len])?;
+            progress = true;
+        }
+
+        progress |= self.drain_sockets()?;
+        progress |= self.drain_listeners();
+        progress |= self.service_connections()?;
+        Ok(progress)
+    }
+
+    /// Send datagrams queued on bound sockets
+    fn drain_sockets(&mut self) -> Result<bool> {
+        let mut outgoing = Vec::new();
+        let mut message = [0u8; socket::MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+
+        self.sockets.retain(|binding| loop {
+            match binding.channel.read(&mut message, &mut handles) {
+                Ok(0) => break true,
+                Ok(len) => match socket::decode(&message[..len]) {
+                    Some((to, op::DATA, payload)) => outgoing.push((binding.port, to, payload.to_vec())),
+                    Some((_, op::CLOSE, _)) => break false,
+                    _ => {}
+                },
+                Err(err) if err.status() == Status::WouldBlock => break true,
+                // The socket was closed
+                Err(_) => break false,
+            }
+        });
+
+        let progress = !outgoing.is_empty();
+        for (port, to, payload) in outgoing {
+            self.send_udp(port, to, &payload)?;
+        }
+        Ok(progress)
+    }
+
+    /// Drop listeners whose owners closed them
+    fn drain_listeners(&mut self) -> bool {
+        let before = self.listeners.len();
+        let mut message = [0u8; socket::MAX_MESSAGE_SIZE];
+        let mut handles = Vec::new();
+        self.listeners.retain(|listener| loop {
+            match listener.channel.read(&mut message, &mut handles) {
+                Ok(0) => break true,
+                Ok(len) => {
+                    if let Some((_, op::CLOSE, _)) = socket::decode(&message[..len]) {
+                        break false;
+                    }
+                }
+                Err(err) if err.status() == Status::WouldBlock => break true,
+                Err(_) => break false,
+            }
+        });
+        self.listeners.len() != before
+    }
+
+    /// Service every TCP connection, dropping those that are done
+    fn service_connections(&mut self) -> Result<bool> {
+        let mut progress = false;
+        let mut i = 0;
+        while i < self.connections.len() {
+            let mut out = Vec::new();
+            let conn = &mut self.connections[i];
+            progress |= conn.service(&self.listeners, self.now, &mut out);
+            let (local, remote) = (conn.tcb.local(), conn.tcb.remote());
+            if conn.finished() {
+                // Dropping the connection closes its channel
+                self.connections.remove(i);
+            } else {
+                i += 1;
+            }
+            if !out.is_empty() {
+                progress = true;
+                self.send_segments(local, remote, out)?;
+            }
+        }
+        Ok(progress)
+    }
+
+    /// ============================================================================
+    /// Receive Path
+    /// ============================================================================
+
+    fn handle_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let (eth, payload) = match EthHeader::parse(frame) {
+            Some(parsed) => parsed,
+            None => return Ok(()),
+        };
+        if eth.dst != self.mac && eth.dst != BROADCAST {
+            return Ok(());
+        }
+        match eth.ethertype {
+            ethertype::ARP => self.handle_arp(payload),
+            ethertype::IPV4 => self.handle_ipv4(payload),
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_arp(&mut self, payload: &[u8]) -> Result<()> {
+        let packet = match ArpPacket::parse(payload) {
+            Some(packet) => packet,
+            None => return Ok(()),
+        };
+
+        // RFC 826: refresh known senders, learn senders addressing us
+        let for_us = packet.target_ip == self.config.ip;
+        if for_us || self.arp.lookup(packet.sender_ip).is_some() {
+            self.arp.insert(packet.sender_ip, packet.sender_mac);
+            self.flush_pending(packet.sender_ip)?;
+        }
+
+        if for_us && packet.op == arp::op::REQUEST {
+            let reply = ArpPacket {
+                op: arp::op::REPLY,
+                sender_mac: self.mac,
+                sender_ip: self.config.ip,
+                target_mac: packet.sender_mac,
+                target_ip: packet.sender_ip,
+            };
+            let mut buf = [0u8; arp::PACKET_SIZE];
+            reply.write(&mut buf);
+            self.send_frame(packet.sender_mac, ethertype::ARP, &buf)?;
+        }
+        Ok(())
+    }
+
+    fn handle_ipv4(&mut self, payload: &[u8]) -> Result<()> {
+        let (ip, data) = match Ipv4Header::parse(payload) {
+            Some(parsed) => parsed,
+            None => return Ok(()),
+        };
+        let broadcast = ip.dst == Ipv4Addr::BROADCAST || ip.dst == self.config.broadcast();
+        if ip.dst != self.config.ip && !broadcast {
+            return Ok(());
+        }
+        match ip.protocol {
+            protocol::ICMP if !broadcast => self.handle_icmp(&ip, data),
+            protocol::TCP if !broadcast => self.handle_tcp(&ip, data),
+            protocol::UDP => {
+                self.handle_udp(&ip, data);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_icmp(&mut self, ip: &Ipv4Header, data: &[u8]) -> Result<()> {
+        let (echo, payload) = match Echo::parse(data) {
+            Some(parsed) => parsed,
+            None => return Ok(()),
+        };
+        match echo.icmp_type {
+            icmp_type::ECHO_REQUEST => {
+                let reply = Echo {
+                    icmp_type: icmp_type::ECHO_REPLY,
+                    ..echo
+                };
+                let mut message = vec![0u8; data.len()];
+                reply.write(payload, &mut message);
+                self.send_ip(ip.src, protocol::ICMP, &message)
+            }
+            _ => {
+                if self.echo_replies.len() == MAX_ECHO_REPLIES {
+                    self.echo_replies.pop_front();
+                }
+                self.echo_replies.push_back(EchoReply {
+                    src: ip.src,
+                    id: echo.id,
+                    seq: echo.seq,
+                    len: payload.len(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_udp(&mut self, ip: &Ipv4Header, data: &[u8]) {
+        let (udp, payload) = match UdpHeader::parse(ip.src, ip.dst, data) {
+            Some(parsed) => parsed,
+            None => return,
+        };
+        let binding = match self.sockets.iter().find(|b| b.port == udp.dst_port) {
+            Some(binding) => binding,
+            None => return,
+        };
+        let from = SocketAddrV4::new(ip.src, udp.src_port);
+        let mut message = vec![0u8; socket::ADDR_HEADER_SIZE + payload.len()];
+        socket::encode(from, op::DATA, payload, &mut message);
+        // A full socket drops the datagram, as UDP may
+        let _ = binding.channel.write(&message, &[]);
+    }
+
+    fn handle_tcp(&mut self, ip: &Ipv4Header, data: &[u8]) -> Result<()> {
+        let (header, payload) = match TcpHeader::parse(ip.src, ip.dst, data) {
+            Some(parsed) => parsed,
+            None => return Ok(()),
+        };
+        let local = SocketAddrV4::new(ip.dst, header.dst_port);
+        let remote = SocketAddrV4::new(ip.src, header.src_port);
+        let now = self.now;
+        let mut out = Vec::new();
+
+        let existing = self
+            .connections
+            .iter_mut()
+            .find(|c| !c.tcb.is_closed() && c.tcb.local() == local && c.tcb.remote() == remote);
+        if let Some(conn) = existing {
+            conn.tcb.on_segment(&header, payload, now, &mut out);
+        } else if header.has(flags::RST) {
+            return Ok(());
+        } else if header.flags & (flags::SYN | flags::ACK) == flags::SYN {
+            let listener = self
+                .listeners
+                .iter()
+                .find(|l| l.port == header.dst_port)
+                .map(|l| (l.port, l.backlog));
+            match listener {
+                None => out.push(Segment::reset(&header, payload.len())),
+                Some((port, backlog)) => {
+                    let queued = self.connections.iter().filter(|c| c.listener == Some(port)).count();
+                    // Past the backlog the SYN is dropped and the peer retries
+                    if queued < backlog && self.connections.len() < MAX_CONNECTIONS {
+                        let iss = self.next_iss();
+                        let tcb = Tcb::accept(local, remote, iss, self.mss(), &header, now, &mut out);
+                        self.connections.push(Connection::new(tcb, None, Some(port)));
+                    }
+                }
+            }
+        } else {
+            out.push(Segment::reset(&header, payload.len()));
+        }
+        self.send_segments(local, remote, out)
+    }
+
+    /// ============================================================================
+    /// Transmit Path
+    /// ============================================================================
+
+    fn send_segments(&mut self, local: SocketAddrV4, remote: SocketAddrV4, segments: Vec<Segment>) -> Result<()> {
+        for segment in segments {
+            let header = segment.header(local.port(), remote.port());
+            let mut buf = vec![0u8; header.header_len() + segment.payload.len()];
+            header.write(*local.ip(), *remote.ip(), &segment.payload, &mut buf);
+            self.send_ip(*remote.ip(), protocol::TCP, &buf)?;
+        }
+        Ok(())
+    }
+
+    fn send_udp(&mut self, src_port: u16, to: SocketAddrV4, payload: &[u8]) -> Result<()> {
+        let header = UdpHeader {
+            src_port,
+            dst_port: to.port(),
+        };
+        let mut datagram = vec![0u8; udp::HEADER_SIZE + payload.len()];
+        header.write(self.config.ip, *to.ip(), payload, &mut datagram);
+        self.send_ip(*to.ip(), protocol::UDP, &datagram)
+    }
+
+    fn send_ip(&mut self, dst: Ipv4Addr, protocol: u8, payload: &[u8]) -> Result<()> {
+        let mtu = self.link.info().mtu as usize;
+        if ipv4::HEADER_SIZE + payload.len() > mtu {
+            return Err(Error::new(Status::InvalidArgs));
+        }
+
+        let header = Ipv4Header {
+            src: self.config.ip,
+            dst,
+            protocol,
+            ttl: ipv4::DEFAULT_TTL,
+            id: self.next_ip_id,
+            payload_len: payload.len() as u16,
+        };
+        self.next_ip_id = self.next_ip_id.wrapping_add(1);
+        let mut packet = vec![0u8; ipv4::HEADER_SIZE + payload.len()];
+        header.write(&mut packet);
+        packet[ipv4::HEADER_SIZE..].copy_from_slice(payload);
+
+        if dst == Ipv4Addr::BROADCAST || dst == self.config.broadcast() {
+            return self.send_frame(BROADCAST, ethertype::IPV4, &packet);
+        }
+
+        let next_hop = if self.config.is_local(dst) { dst } else { self.config.gateway };
+        match self.arp.lookup(next_hop) {
+            Some(mac) => self.send_frame(mac, ethertype::IPV4, &packet),
+            None => {
+                if self.pending.len() == MAX_PENDING {
+                    self.pending.pop_front();
+                }
+                self.pending.push_back(Pending { next_hop, packet });
+                self.send_arp_request(next_hop)
+            }
+        }
+    }
+
+    fn send_arp_request(&mut self, ip: Ipv4Addr) -> Result<()> {
+        let request = ArpPacket {
+            op: arp::op::REQUEST,
+            sender_mac: self.mac,
+            sender_ip: self.config.ip,
+            target_mac: [0; 6],
+            target_ip: ip,
+        };
+        let mut buf = [0u8; arp::PACKET_SIZE];
+        request.write(&mut buf);
+        self.send_frame(BROADCAST, ethertype::ARP, &buf)
+    }
+
+    /// Send packets that were waiting for `ip` to resolve
+    fn flush_pending(&mut self, ip: Ipv4Addr) -> Result<()> {
+        let mac = match self.arp.lookup(ip) {
+            Some(mac) => mac,
+            None => return Ok(()),
+        };
+        let mut ready = Vec::new();
+        self.pending.retain_mut(|pending| {
+            if pending.next_hop == ip {
+                ready.push(core::mem::take(&mut pending.packet));
+                false
+            } else {
+                true
+            }
+        });
+        for packet in ready {
+            self.send_frame(mac, ethertype::IPV4, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn send_frame(&mut self, dst: MacAddr, ethertype: u16, payload: &[u8]) -> Result<()> {
+        let len = (ethernet::HEADER_SIZE + payload.len()).max(MIN_FRAME_SIZE);
+        let mut frame = vec![0u8; len];
+        let header = EthHeader {
+            dst,
+            src: self.mac,
+            ethertype,
+        };
+        let offset = header.write(&mut frame);
+        frame[offset..offset + payload.len()].copy_from_slice(payload);
+        self.link.send(&frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libddk::EthernetInfo;
+
+    const MAC: MacAddr = [0x02, 0, 0, 0, 0, 1];
+    const GATEWAY_MAC: MacAddr = [0x52, 0x55, 0x0a, 0, 2, 2];
+
+    #[derive(Default)]
+    struct FakeLink {
+        rx: VecDeque<Vec<u8>>,
+        tx: Vec<Vec<u8>>,
+    }
+
+    impl EthernetDevice for FakeLink {
+        fn info(&self) -> EthernetInfo {
+            EthernetInfo { mac: MAC, mtu: 1500 }
+        }
+
+        fn send(&mut self, frame: &[u8]) -> Result<()> {
+            self.tx.push(frame.to_vec());
+            Ok(())
+        }
+
+        fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+            match self.rx.pop_front() {
+                Some(frame) => {
+                    buf[..frame.len()].copy_from_slice(&frame);
+                    Ok(frame.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    fn frame(dst: MacAddr, ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; ethernet::HEADER_SIZE + payload.len()];
+        EthHeader {
+            dst,
+            src: GATEWAY_MAC,
+            ethertype,
+        }
+        .write(&mut frame);
+        frame[ethernet::HEADER_SIZE..].copy_from_slice(payload);
+        frame
+    }
+
+    fn arp_frame(op: u16, target_mac: MacAddr) -> Vec<u8> {
+        let packet = ArpPacket {
+            op,
+            sender_mac: GATEWAY_MAC,
+            sender_ip: Config::qemu_user().gateway,
+            target_mac,
+            target_ip: Config::qemu_user().ip,
+        };
+        let mut buf = [0u8; arp::PACKET_SIZE];
+        packet.write(&mut buf);
+        frame(if op == arp::op::REQUEST { BROADCAST } else { MAC }, ethertype::ARP, &buf)
+    }
+
+    #[test]
+    fn test_answers_arp_request() {
+        let mut stack = Stack::new(FakeLink::default(), Config::qemu_user());
+        stack.link.rx.push_back(arp_frame(arp::op::REQUEST, [0; 6]));
+        assert!(stack.poll().unwrap());
+
+        assert_eq!(stack.link.tx.len(), 1);
+        let (eth, payload) = EthHeader::parse(&stack.link.tx[0]).unwrap();
+        assert_eq!(eth.dst, GATEWAY_MAC);
+        let reply = ArpPacket::parse(payload).unwrap();
+        assert_eq!(reply.op, arp::op::REPLY);
+        assert_eq!(reply.sender_mac, MAC);
+        assert_eq!(reply.target_ip, Config::qemu_user().gateway);
+    }
+
+    #[test]
+    fn test_answers_ping_after_resolving() {
+        let config = Config::qemu_user();
+        let mut stack = Stack::new(FakeLink::default(), config);
+
+        // Echo request from the gateway
+        let mut icmp = [0u8; 12];
+        Echo {
+            icmp_type: icmp_type::ECHO_REQUEST,
+            id: 7,
+            seq: 1,
+        }
+        .write(b"ping", &mut icmp);
+        let mut packet = [0u8; ipv4::HEADER_SIZE + 12];
+        Ipv4Header {
+            src: config.gateway,
+            dst: config.ip,
+            protocol: protocol::ICMP,
+            ttl: 64,
+            id: 1,
+            payload_len: 12,
+        }
+        .write(&mut packet);
+        packet[ipv4::HEADER_SIZE..].copy_from_slice(&icmp);
+        stack.link.rx.push_back(frame(MAC, ethertype::IPV4, &packet));
+
+        // The reply waits for ARP
+        stack.poll().unwrap();
+        assert_eq!(stack.link.tx.len(), 1);
+        let (eth, _) = EthHeader::parse(&stack.link.tx[0]).unwrap();
+        assert_eq!((eth.dst, eth.ethertype), (BROADCAST, ethertype::ARP));
+
+        stack.link.rx.push_back(arp_frame(arp::op::REPLY, MAC));
+        stack.poll().unwrap();
+        assert_eq!(stack.link.tx.len(), 2);
+
+        let (eth, payload) = EthHeader::parse(&stack.link.tx[1]).unwrap();
+        assert_eq!((eth.dst, eth.ethertype), (GATEWAY_MAC, ethertype::IPV4));
+        let (ip, data) = Ipv4Header::parse(payload).unwrap();
+        assert_eq!(ip.dst, config.gateway);
+        let (echo, payload) = Echo::parse(data).unwrap();
+        assert_eq!((echo.icmp_type, echo.id, echo.seq), (icmp_type::ECHO_REPLY, 7, 1));
+        assert_eq!(payload, b"ping");
+    }
+
+    #[test]
+    fn test_config_routing() {
+        let config = Config::qemu_user();
+        assert!(config.is_local(Ipv4Addr::new(10, 0, 2, 3)));
+        assert!(!config.is_local(Ipv4Addr::new(8, 8, 8, 8)));
+        assert_eq!(config.broadcast(), Ipv4Addr::new(10, 0, 2, 255));
+    }
+
+    #[test]
+    fn test_resets_closed_port() {
+        let config = Config::qemu_user();
+        let mut stack = Stack::new(FakeLink::default(), config);
+
+        // Learn the gateway first so the reset goes straight out
+        stack.link.rx.push_back(arp_frame(arp::op::REQUEST, [0; 6]));
+        stack.poll().unwrap();
+        stack.link.tx.clear();
+
+        let syn = TcpHeader {
+            src_port: 5555,
+            dst_port: 80,
+            seq: 41,
+            ack: 0,
+            flags: flags::SYN,
+            window: 8192,
+            mss: Some(1460),
+        };
+        let mut segment = [0u8; tcp::SYN_HEADER_SIZE];
+        syn.write(config.gateway, config.ip, &[], &mut segment);
+        let mut packet = [0u8; ipv4::HEADER_SIZE + tcp::SYN_HEADER_SIZE];
+        Ipv4Header {
+            src: config.gateway,
+            dst: config.ip,
+            protocol: protocol::TCP,
+            ttl: 64,
+            id: 2,
+            payload_len: tcp::SYN_HEADER_SIZE as u16,
+        }
+        .write(&mut packet);
+        packet[ipv4::HEADER_SIZE..].copy_from_slice(&segment);
+        stack.link.rx.push_back(frame(MAC, ethertype::IPV4, &packet));
+        stack.poll().unwrap();
+
+        assert_eq!(stack.link.tx.len(), 1);
+        let (_, payload) = EthHeader::parse(&stack.link.tx[0]).unwrap();
+        let (ip, data) = Ipv4Header::parse(payload).unwrap();
+        let (reset, _) = TcpHeader::parse(ip.src, ip.dst, data).unwrap();
+        assert_eq!((reset.src_port, reset.dst_port), (80, 5555));
+        assert_eq!((reset.flags, reset.ack), (flags::RST | flags::ACK, 42));
+    }
+}
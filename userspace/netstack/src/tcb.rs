@@ -0,0 +1,1009 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TCP Connections
+//!
+//! `Tcb` is one connection's transmission control block: the RFC 793
+//! state machine with RFC 6298 retransmission timing. It does no I/O.
+//! Received segments go in through `on_segment`, timers advance through
+//! `poll`, and whatever must be sent is appended to an output list for
+//! the stack to frame. Times are milliseconds on any monotonic clock.
+//!
+//! Simplifications:
+//! - No congestion control; the sender is bounded by the peer's window
+//! - No window scaling, timestamps or selective acknowledgement
+//! - Every data segment is acknowledged immediately
+//! - Out-of-order segments are held in a short list and merged as the
+//!   gap fills; anything beyond it is dropped and left to retransmission
+//!
+//! Connections that end abnormally record why in `error`:
+//! `Status::NotFound` when refused, `Status::HandleClosed` when reset and
+//! `Status::TimedOut` when retransmission gives up.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::net::SocketAddrV4;
+
+use libsys::Status;
+
+use crate::tcp::{flags, seq_le, seq_lt, TcpHeader, DEFAULT_MSS};
+
+/// Bytes the application may queue for sending
+pub const SEND_BUFFER_SIZE: usize = 16384;
+
+/// Bytes buffered for the application, and so the largest window offered
+pub const RECV_BUFFER_SIZE: usize = 16384;
+
+/// Retransmission timeout before any round trip is measured
+pub const INITIAL_RTO: u64 = 1000;
+
+/// Bounds on the retransmission timeout
+pub const MIN_RTO: u64 = 200;
+pub const MAX_RTO: u64 = 60_000;
+
+/// Consecutive timeouts before the connection is dropped
+pub const MAX_RETRIES: u32 = 8;
+
+/// Maximum segment lifetime
+pub const MSL: u64 = 30_000;
+
+/// Out-of-order segments held for reassembly
+const MAX_OUT_OF_ORDER: usize = 8;
+
+/// Connection states (RFC 793 section 3.2, less LISTEN)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+    Closed,
+}
+
+/// A segment for the stack to send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub seq: u32,
+    pub ack: u32,
+    pub flags: u8,
+    pub window: u16,
+    pub mss: Option<u16>,
+    pub payload: Vec<u8>,
+}
+
+impl Segment {
+    /// The reset answering `header` when it matches no connection
+    pub fn reset(header: &TcpHeader, payload_len: usize) -> Self {
+        if header.has(flags::ACK) {
+            return Self::control(header.ack, 0, flags::RST);
+        }
+        let len = payload_len as u32 + header.has(flags::SYN) as u32 + header.has(flags::FIN) as u32;
+        Self::control(0, header.seq.wrapping_add(len), flags::RST | flags::ACK)
+    }
+
+    fn control(seq: u32, ack: u32, flags: u8) -> Self {
+        Self {
+            seq,
+            ack,
+            flags,
+            window: 0,
+            mss: None,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Header for this segment from `src_port` to `dst_port`
+    pub fn header(&self, src_port: u16, dst_port: u16) -> TcpHeader {
+        TcpHeader {
+            src_port,
+            dst_port,
+            seq: self.seq,
+            ack: self.ack,
+            flags: self.flags,
+            window: self.window,
+            mss: self.mss,
+        }
+    }
+}
+
+/// Retransmission timeout estimator (RFC 6298)
+#[derive(Debug, Clone, Copy)]
+pub struct Rto {
+    srtt: Option<u64>,
+    rttvar: u64,
+    rto: u64,
+}
+
+impl Rto {
+    pub fn new() -> Self {
+        Self {
+            srtt: None,
+            rttvar: 0,
+            rto: INITIAL_RTO,
+        }
+    }
+
+    /// Current timeout
+    pub fn timeout(&self) -> u64 {
+        self.rto
+    }
+
+    /// Smoothed round-trip time, once measured
+    pub fn srtt(&self) -> Option<u64> {
+        self.srtt
+    }
+
+    /// Fold in a measured round trip
+    pub fn sample(&mut self, rtt: u64) {
+        let srtt = match self.srtt {
+            None => {
+                self.rttvar = rtt / 2;
+                rtt
+            }
+            Some(srtt) => {
+                self.rttvar = (3 * self.rttvar + srtt.abs_diff(rtt)) / 4;
+                (7 * srtt + rtt) / 8
+            }
+        };
+        self.srtt = Some(srtt);
+        self.rto = (srtt + (4 * self.rttvar).max(1)).clamp(MIN_RTO, MAX_RTO);
+    }
+
+    /// Double the timeout after it expired
+    pub fn backoff(&mut self) {
+        self.rto = (self.rto * 2).min(MAX_RTO);
+    }
+}
+
+impl Default for Rto {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Transmission control block
+pub struct Tcb {
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    state: State,
+
+    /// Our MSS and the one used for sending
+    local_mss: u16,
+    mss: u16,
+
+    // Send sequence space. `send_buf` holds every byte from `snd_una`
+    // on, sent or not.
+    iss: u32,
+    snd_una: u32,
+    snd_nxt: u32,
+    snd_wnd: u32,
+    snd_wl1: u32,
+    snd_wl2: u32,
+    send_buf: VecDeque<u8>,
+    fin_queued: bool,
+    fin_sent: bool,
+
+    // Receive sequence space
+    rcv_nxt: u32,
+    recv_buf: VecDeque<u8>,
+    out_of_order: Vec<(u32, Vec<u8>)>,
+    fin_received: bool,
+    ack_pending: bool,
+
+    // Timers
+    rto: Rto,
+    retransmit_at: Option<u64>,
+    retries: u32,
+    rtt_probe: Option<(u32, u64)>,
+    time_wait_until: Option<u64>,
+
+    error: Option<Status>,
+}
+
+impl Tcb {
+    fn new(local: SocketAddrV4, remote: SocketAddrV4, iss: u32, mss: u16, state: State) -> Self {
+        Self {
+            local,
+            remote,
+            state,
+            local_mss: mss,
+            mss: DEFAULT_MSS.min(mss),
+            iss,
+            snd_una: iss,
+            snd_nxt: iss.wrapping_add(1),
+            snd_wnd: 0,
+            snd_wl1: 0,
+            snd_wl2: 0,
+            send_buf: VecDeque::new(),
+            fin_queued: false,
+            fin_sent: false,
+            rcv_nxt: 0,
+            recv_buf: VecDeque::new(),
+            out_of_order: Vec::new(),
+            fin_received: false,
+            ack_pending: false,
+            rto: Rto::new(),
+            retransmit_at: None,
+            retries: 0,
+            rtt_probe: None,
+            time_wait_until: None,
+            error: None,
+        }
+    }
+
+    /// Start an active open, sending SYN
+    pub fn connect(
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+        iss: u32,
+        mss: u16,
+        now: u64,
+        out: &mut Vec<Segment>,
+    ) -> Self {
+        let mut tcb = Self::new(local, remote, iss, mss, State::SynSent);
+        tcb.send_syn(now, out);
+        tcb
+    }
+
+    /// Answer a SYN received on a listening port, sending SYN-ACK
+    pub fn accept(
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+        iss: u32,
+        mss: u16,
+        syn: &TcpHeader,
+        now: u64,
+        out: &mut Vec<Segment>,
+    ) -> Self {
+        let mut tcb = Self::new(local, remote, iss, mss, State::SynReceived);
+        tcb.rcv_nxt = syn.seq.wrapping_add(1);
+        tcb.mss = syn.mss.unwrap_or(DEFAULT_MSS).min(mss);
+        tcb.snd_wnd = syn.window as u32;
+        tcb.snd_wl1 = syn.seq;
+        tcb.send_syn(now, out);
+        tcb
+    }
+
+    /// ============================================================================
+    /// Status
+    /// ============================================================================
+
+    pub fn local(&self) -> SocketAddrV4 {
+        self.local
+    }
+
+    pub fn remote(&self) -> SocketAddrV4 {
+        self.remote
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Why the connection ended, if not by an orderly close
+    pub fn error(&self) -> Option<Status> {
+        self.error
+    }
+
+    /// Whether the handshake has completed and the connection is open
+    pub fn is_synchronized(&self) -> bool {
+        !matches!(self.state, State::SynSent | State::SynReceived | State::Closed)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    /// Retransmission timer state
+    pub fn rto(&self) -> &Rto {
+        &self.rto
+    }
+
+    /// ============================================================================
+    /// Application Interface
+    /// ============================================================================
+
+    /// Bytes `send` would currently accept
+    pub fn send_space(&self) -> usize {
+        let open = matches!(
+            self.state,
+            State::SynSent | State::SynReceived | State::Established | State::CloseWait
+        );
+        if open && !self.fin_queued {
+            SEND_BUFFER_SIZE - self.send_buf.len()
+        } else {
+            0
+        }
+    }
+
+    /// Queue data for sending, returning how much was taken
+    pub fn send(&mut self, data: &[u8]) -> usize {
+        let n = data.len().min(self.send_space());
+        self.send_buf.extend(&data[..n]);
+        n
+    }
+
+    /// Bytes received and not yet consumed
+    pub fn readable(&self) -> usize {
+        self.recv_buf.len()
+    }
+
+    /// Copy received data into `buf` without consuming it
+    pub fn peek(&self, buf: &mut [u8]) -> usize {
+        let n = buf.len().min(self.recv_buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.recv_buf.iter()) {
+            *dst = *src;
+        }
+        n
+    }
+
+    /// Drop `n` received bytes, opening the window
+    pub fn consume(&mut self, n: usize) {
+        let before = self.window();
+        self.recv_buf.drain(..n.min(self.recv_buf.len()));
+        // Announce a window that reopens to a full segment
+        if before < self.mss && self.window() >= self.mss {
+            self.ack_pending = true;
+        }
+    }
+
+    /// Read and consume received data
+    pub fn recv(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.peek(buf);
+        self.consume(n);
+        n
+    }
+
+    /// Whether the peer has finished sending and everything was read
+    pub fn at_eof(&self) -> bool {
+        self.fin_received && self.recv_buf.is_empty()
+    }
+
+    /// Close our side once queued data has been sent
+    pub fn shutdown(&mut self) {
+        if self.state == State::SynSent {
+            self.close_now();
+        }
+        self.fin_queued = true;
+    }
+
+    /// Drop the connection, resetting the peer
+    pub fn abort(&mut self, out: &mut Vec<Segment>) {
+        if !matches!(self.state, State::SynSent | State::TimeWait | State::Closed) {
+            out.push(Segment::control(self.snd_nxt, 0, flags::RST));
+        }
+        self.close_now();
+    }
+
+    /// ============================================================================
+    /// Input
+    /// ============================================================================
+
+    /// Process a segment addressed to this connection
+    pub fn on_segment(&mut self, header: &TcpHeader, payload: &[u8], now: u64, out: &mut Vec<Segment>) {
+        match self.state {
+            State::Closed => return,
+            State::SynSent => return self.on_syn_sent(header, now, out),
+            _ => {}
+        }
+
+        if !self.acceptable(header, payload.len()) {
+            if !header.has(flags::RST) {
+                if self.state == State::SynReceived {
+                    out.push(self.syn_segment());
+                } else {
+                    self.ack_pending = true;
+                    self.output(now, out);
+                }
+            }
+            return;
+        }
+
+        if header.has(flags::RST) {
+            self.error = Some(Status::HandleClosed);
+            self.close_now();
+            return;
+        }
+
+        // A SYN inside the window means the peer lost this connection
+        if header.has(flags::SYN) {
+            self.error = Some(Status::HandleClosed);
+            self.abort(out);
+            return;
+        }
+
+        if !header.has(flags::ACK) {
+            return;
+        }
+
+        if self.state == State::SynReceived {
+            if !(seq_lt(self.snd_una, header.ack) && seq_le(header.ack, self.snd_nxt)) {
+                out.push(Segment::control(header.ack, 0, flags::RST));
+                return;
+            }
+            self.state = State::Established;
+            self.snd_wnd = header.window as u32;
+            self.snd_wl1 = header.seq;
+            self.snd_wl2 = header.ack;
+        }
+
+        self.on_ack(header, now);
+        let fin_acked = self.fin_sent && self.snd_una == self.snd_nxt;
+        match self.state {
+            State::FinWait1 if fin_acked => self.state = State::FinWait2,
+            State::Closing if fin_acked => self.enter_time_wait(now),
+            State::LastAck if fin_acked => {
+                self.close_now();
+                return;
+            }
+            _ => {}
+        }
+
+        if !payload.is_empty() {
+            self.on_data(header.seq, payload);
+        }
+
+        if header.has(flags::FIN) {
+            let fin_seq = header.seq.wrapping_add(payload.len() as u32);
+            if self.fin_received {
+                // Our ACK of it was lost
+                self.ack_pending = true;
+                if self.state == State::TimeWait {
+                    self.enter_time_wait(now);
+                }
+            } else if fin_seq == self.rcv_nxt {
+                self.on_fin(now);
+            }
+        }
+
+        self.output(now, out);
+    }
+
+    fn on_syn_sent(&mut self, header: &TcpHeader, now: u64, out: &mut Vec<Segment>) {
+        let has_ack = header.has(flags::ACK);
+        let ack_ok = has_ack && seq_lt(self.iss, header.ack) && seq_le(header.ack, self.snd_nxt);
+        if has_ack && !ack_ok {
+            if !header.has(flags::RST) {
+                out.push(Segment::control(header.ack, 0, flags::RST));
+            }
+            return;
+        }
+        if header.has(flags::RST) {
+            if ack_ok {
+                self.error = Some(Status::NotFound);
+                self.close_now();
+            }
+            return;
+        }
+        if !header.has(flags::SYN) {
+            return;
+        }
+
+        self.rcv_nxt = header.seq.wrapping_add(1);
+        self.mss = header.mss.unwrap_or(DEFAULT_MSS).min(self.local_mss);
+        if ack_ok {
+            self.on_ack(header, now);
+            self.snd_wnd = header.window as u32;
+            self.snd_wl1 = header.seq;
+            self.snd_wl2 = header.ack;
+            self.state = State::Established;
+            self.ack_pending = true;
+            self.output(now, out);
+        } else {
+            // Simultaneous open
+            self.state = State::SynReceived;
+            out.push(self.syn_segment());
+        }
+    }
+
+    /// Sequence acceptability test (RFC 793 section 3.3)
+    fn acceptable(&self, header: &TcpHeader, payload_len: usize) -> bool {
+        let seg_len =
+            payload_len as u32 + header.has(flags::SYN) as u32 + header.has(flags::FIN) as u32;
+        let window = self.window() as u32;
+        let in_window = |seq: u32| seq_le(self.rcv_nxt, seq) && seq_lt(seq, self.rcv_nxt.wrapping_add(window));
+
+        if window == 0 {
+            // Still take ACKs and RSTs; data is trimmed away later
+            header.seq == self.rcv_nxt
+        } else if seg_len == 0 {
+            in_window(header.seq)
+        } else {
+            in_window(header.seq) || in_window(header.seq.wrapping_add(seg_len - 1))
+        }
+    }
+
+    fn on_ack(&mut self, header: &TcpHeader, now: u64) {
+        let ack = header.ack;
+        if seq_lt(self.snd_una, ack) && seq_le(ack, self.snd_nxt) {
+            let mut acked = ack.wrapping_sub(self.snd_una) as usize;
+            if self.snd_una == self.iss {
+                // The SYN
+                acked -= 1;
+            }
+            let data = acked.min(self.send_buf.len());
+            self.send_buf.drain(..data);
+            self.snd_una = ack;
+
+            if let Some((seq, sent)) = self.rtt_probe {
+                if seq_lt(seq, ack) {
+                    self.rto.sample(now.saturating_sub(sent));
+                    self.rtt_probe = None;
+                }
+            }
+            self.retries = 0;
+            self.retransmit_at = if self.snd_una == self.snd_nxt {
+                None
+            } else {
+                Some(now + self.rto.timeout())
+            };
+        } else if seq_lt(self.snd_nxt, ack) {
+            // Acknowledges something never sent
+            self.ack_pending = true;
+            return;
+        } else if ack == self.snd_una && header.window == 0 {
+            // The peer is answering window probes
+            self.retries = 0;
+        }
+
+        if seq_lt(self.snd_wl1, header.seq) || (self.snd_wl1 == header.seq && seq_le(self.snd_wl2, ack)) {
+            self.snd_wnd = header.window as u32;
+            self.snd_wl1 = header.seq;
+            self.snd_wl2 = ack;
+        }
+    }
+
+    fn on_data(&mut self, seq: u32, payload: &[u8]) {
+        if !matches!(self.state, State::Established | State::FinWait1 | State::FinWait2) {
+            return;
+        }
+        self.ack_pending = true;
+
+        // Trim what was already received, then what overflows the window
+        let mut seq = seq;
+        let mut data = payload;
+        if seq_lt(seq, self.rcv_nxt) {
+            let skip = self.rcv_nxt.wrapping_sub(seq) as usize;
+            if skip >= data.len() {
+                return;
+            }
+            data = &data[skip..];
+            seq = self.rcv_nxt;
+        }
+        let offset = seq.wrapping_sub(self.rcv_nxt) as usize;
+        let window = self.window() as usize;
+        if offset >= window {
+            return;
+        }
+        data = &data[..data.len().min(window - offset)];
+
+        if offset == 0 {
+            self.recv_buf.extend(data);
+            self.rcv_nxt = self.rcv_nxt.wrapping_add(data.len() as u32);
+            self.reassemble();
+        } else if self.out_of_order.len() < MAX_OUT_OF_ORDER
+            && !self.out_of_order.iter().any(|(s, _)| *s == seq)
+        {
+            self.out_of_order.push((seq, data.to_vec()));
+        }
+    }
+
+    /// Append held segments that now continue the stream
+    fn reassemble(&mut self) {
+        loop {
+            let rcv_nxt = self.rcv_nxt;
+            self.out_of_order
+                .retain(|(seq, data)| seq_lt(rcv_nxt, seq.wrapping_add(data.len() as u32)));
+            let next = self.out_of_order.iter().position(|(seq, _)| seq_le(*seq, rcv_nxt));
+            let (seq, data) = match next {
+                Some(i) => self.out_of_order.swap_remove(i),
+                None => break,
+            };
+            let skip = rcv_nxt.wrapping_sub(seq) as usize;
+            self.recv_buf.extend(&data[skip..]);
+            self.rcv_nxt = rcv_nxt.wrapping_add((data.len() - skip) as u32);
+        }
+    }
+
+    fn on_fin(&mut self, now: u64) {
+        self.rcv_nxt = self.rcv_nxt.wrapping_add(1);
+        self.fin_received = true;
+        self.ack_pending = true;
+        match self.state {
+            State::Established => self.state = State::CloseWait,
+            State::FinWait1 if self.snd_una == self.snd_nxt => self.enter_time_wait(now),
+            State::FinWait1 => self.state = State::Closing,
+            State::FinWait2 => self.enter_time_wait(now),
+            _ => {}
+        }
+    }
+
+    /// ============================================================================
+    /// Output and Timers
+    /// ============================================================================
+
+    /// Run timers and send whatever is ready
+    pub fn poll(&mut self, now: u64, out: &mut Vec<Segment>) {
+        match self.state {
+            State::Closed => return,
+            State::TimeWait => {
+                if self.time_wait_until.is_some_and(|until| now >= until) {
+                    self.close_now();
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        if self.retransmit_at.is_some_and(|at| now >= at) {
+            self.retries += 1;
+            if self.retries > MAX_RETRIES {
+                self.error = Some(Status::TimedOut);
+                self.abort(out);
+                return;
+            }
+            // Karn's algorithm: retransmitted segments give no samples
+            self.rto.backoff();
+            self.rtt_probe = None;
+            self.retransmit_at = Some(now + self.rto.timeout());
+            self.retransmit(out);
+        }
+
+        self.output(now, out);
+    }
+
+    /// Resend the oldest unacknowledged segment
+    fn retransmit(&mut self, out: &mut Vec<Segment>) {
+        match self.state {
+            State::SynSent | State::SynReceived => out.push(self.syn_segment()),
+            State::Established | State::CloseWait | State::FinWait1 | State::Closing | State::LastAck => {
+                let in_flight = self.snd_nxt.wrapping_sub(self.snd_una) as usize;
+                let data = (in_flight - self.fin_sent as usize).min(self.send_buf.len());
+                if data == 0 && self.fin_sent {
+                    out.push(self.segment(self.snd_nxt.wrapping_sub(1), flags::FIN | flags::ACK, Vec::new()));
+                } else if data == 0 && !self.send_buf.is_empty() {
+                    // Probe a zero window with one byte
+                    let probe = self.send_buf.iter().take(1).copied().collect();
+                    out.push(self.segment(self.snd_una, flags::ACK, probe));
+                    self.snd_nxt = self.snd_una.wrapping_add(1);
+                } else if data > 0 {
+                    let n = data.min(self.mss as usize);
+                    let mut seg_flags = flags::ACK | flags::PSH;
+                    if self.fin_sent && n == data {
+                        seg_flags |= flags::FIN;
+                    }
+                    let payload = self.send_buf.iter().take(n).copied().collect();
+                    out.push(self.segment(self.snd_una, seg_flags, payload));
+                } else {
+                    self.retransmit_at = None;
+                }
+            }
+            _ => self.retransmit_at = None,
+        }
+    }
+
+    /// Send new data, a queued FIN, or a pending ACK
+    fn output(&mut self, now: u64, out: &mut Vec<Segment>) {
+        let mut sent = false;
+
+        if matches!(self.state, State::Established | State::CloseWait) {
+            loop {
+                let in_flight = self.snd_nxt.wrapping_sub(self.snd_una) as usize;
+                let unsent = self.send_buf.len().saturating_sub(in_flight);
+                let usable = (self.snd_wnd as usize).saturating_sub(in_flight);
+                let n = unsent.min(usable).min(self.mss as usize);
+                if n == 0 {
+                    if unsent > 0 && self.snd_wnd == 0 && self.retransmit_at.is_none() {
+                        // Arm the persist timer
+                        self.retransmit_at = Some(now + self.rto.timeout());
+                    }
+                    break;
+                }
+                let payload = self.send_buf.range(in_flight..in_flight + n).copied().collect();
+                let seg_flags = if n == unsent { flags::ACK | flags::PSH } else { flags::ACK };
+                self.transmit(self.snd_nxt, seg_flags, payload, now, out);
+                self.snd_nxt = self.snd_nxt.wrapping_add(n as u32);
+                sent = true;
+            }
+
+            let in_flight = self.snd_nxt.wrapping_sub(self.snd_una) as usize;
+            if self.fin_queued && !self.fin_sent && in_flight >= self.send_buf.len() {
+                self.transmit(self.snd_nxt, flags::FIN | flags::ACK, Vec::new(), now, out);
+                self.snd_nxt = self.snd_nxt.wrapping_add(1);
+                self.fin_sent = true;
+                self.state = if self.state == State::Established { State::FinWait1 } else { State::LastAck };
+                sent = true;
+            }
+        }
+
+        if self.ack_pending && !sent {
+            out.push(self.segment(self.snd_nxt, flags::ACK, Vec::new()));
+        }
+        self.ack_pending = false;
+    }
+
+    /// Send a sequence-consuming segment, arming the timers
+    fn transmit(&mut self, seq: u32, flags: u8, payload: Vec<u8>, now: u64, out: &mut Vec<Segment>) {
+        if self.retransmit_at.is_none() {
+            self.retransmit_at = Some(now + self.rto.timeout());
+        }
+        if self.rtt_probe.is_none() && self.retries == 0 {
+            self.rtt_probe = Some((seq, now));
+        }
+        out.push(self.segment(seq, flags, payload));
+    }
+
+    fn send_syn(&mut self, now: u64, out: &mut Vec<Segment>) {
+        self.retransmit_at = Some(now + self.rto.timeout());
+        self.rtt_probe = Some((self.iss, now));
+        out.push(self.syn_segment());
+    }
+
+    fn syn_segment(&self) -> Segment {
+        let flags = if self.state == State::SynReceived { flags::SYN | flags::ACK } else { flags::SYN };
+        Segment {
+            mss: Some(self.local_mss),
+            ..self.segment(self.iss, flags, Vec::new())
+        }
+    }
+
+    fn segment(&self, seq: u32, flags: u8, payload: Vec<u8>) -> Segment {
+        Segment {
+            seq,
+            ack: if flags & flags::ACK != 0 { self.rcv_nxt } else { 0 },
+            flags,
+            window: self.window(),
+            mss: None,
+            payload,
+        }
+    }
+
+    /// Receive window on offer
+    fn window(&self) -> u16 {
+        RECV_BUFFER_SIZE.saturating_sub(self.recv_buf.len()).min(u16::MAX as usize) as u16
+    }
+
+    fn enter_time_wait(&mut self, now: u64) {
+        self.state = State::TimeWait;
+        self.retransmit_at = None;
+        self.time_wait_until = Some(now + 2 * MSL);
+    }
+
+    fn close_now(&mut self) {
+        self.state = State::Closed;
+        self.retransmit_at = None;
+        self.time_wait_until = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::Ipv4Addr;
+
+    const ISS: u32 = 1000;
+    const PEER_ISS: u32 = 0xffff_fff0;
+    const MSS: u16 = 1460;
+
+    fn local() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 15), 49152)
+    }
+
+    fn remote() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 2), 80)
+    }
+
+    fn peer(seq: u32, ack: u32, flags: u8) -> TcpHeader {
+        TcpHeader {
+            src_port: 80,
+            dst_port: 49152,
+            seq,
+            ack,
+            flags,
+            window: 8192,
+            mss: None,
+        }
+    }
+
+    /// An established connection; the peer's next sequence is PEER_ISS + 1
+    fn established(out: &mut Vec<Segment>) -> Tcb {
+        let mut tcb = Tcb::connect(local(), remote(), ISS, MSS, 0, out);
+        let syn_ack = TcpHeader {
+            mss: Some(MSS),
+            ..peer(PEER_ISS, ISS + 1, flags::SYN | flags::ACK)
+        };
+        tcb.on_segment(&syn_ack, &[], 10, out);
+        out.clear();
+        tcb
+    }
+
+    #[test]
+    fn test_active_open_and_transfer() {
+        let mut out = Vec::new();
+        let mut tcb = Tcb::connect(local(), remote(), ISS, MSS, 0, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!((out[0].seq, out[0].flags, out[0].mss), (ISS, flags::SYN, Some(MSS)));
+        out.clear();
+
+        let syn_ack = TcpHeader {
+            mss: Some(MSS),
+            ..peer(PEER_ISS, ISS + 1, flags::SYN | flags::ACK)
+        };
+        tcb.on_segment(&syn_ack, &[], 10, &mut out);
+        assert_eq!(tcb.state(), State::Established);
+        assert_eq!(out.len(), 1);
+        assert_eq!((out[0].flags, out[0].ack), (flags::ACK, PEER_ISS.wrapping_add(1)));
+        assert_eq!(tcb.rto().srtt(), Some(10));
+        out.clear();
+
+        assert_eq!(tcb.send(b"hello"), 5);
+        tcb.poll(20, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!((out[0].seq, out[0].payload.as_slice()), (ISS + 1, &b"hello"[..]));
+        out.clear();
+
+        tcb.on_segment(&peer(PEER_ISS.wrapping_add(1), ISS + 6, flags::ACK), &[], 30, &mut out);
+        assert!(out.is_empty());
+        assert_eq!(tcb.send_space(), SEND_BUFFER_SIZE);
+        tcb.poll(10_000, &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_passive_open() {
+        let mut out = Vec::new();
+        let syn = TcpHeader {
+            mss: Some(1000),
+            ..peer(PEER_ISS, 0, flags::SYN)
+        };
+        let mut tcb = Tcb::accept(local(), remote(), ISS, MSS, &syn, 0, &mut out);
+        assert_eq!(out[0].flags, flags::SYN | flags::ACK);
+        assert_eq!(out[0].ack, PEER_ISS.wrapping_add(1));
+        out.clear();
+
+        // A retransmitted SYN gets the SYN-ACK again
+        tcb.on_segment(&syn, &[], 5, &mut out);
+        assert_eq!(out[0].flags, flags::SYN | flags::ACK);
+        out.clear();
+
+        tcb.on_segment(&peer(PEER_ISS.wrapping_add(1), ISS + 1, flags::ACK), &[], 10, &mut out);
+        assert_eq!(tcb.state(), State::Established);
+        assert!(tcb.is_synchronized());
+
+        // Sends are bounded by the peer's MSS
+        tcb.send(&[0u8; 2500]);
+        tcb.poll(11, &mut out);
+        let sizes: Vec<usize> = out.iter().map(|s| s.payload.len()).collect();
+        assert_eq!(sizes, [1000, 1000, 500]);
+    }
+
+    #[test]
+    fn test_retransmits_with_backoff() {
+        let mut out = Vec::new();
+        let mut tcb = Tcb::connect(local(), remote(), ISS, MSS, 0, &mut out);
+        out.clear();
+
+        tcb.poll(INITIAL_RTO - 1, &mut out);
+        assert!(out.is_empty());
+
+        let mut now = INITIAL_RTO;
+        let mut timeout = INITIAL_RTO;
+        for _ in 0..MAX_RETRIES {
+            tcb.poll(now, &mut out);
+            assert_eq!(out.len(), 1);
+            assert_eq!(out[0].flags, flags::SYN);
+            out.clear();
+            timeout = (timeout * 2).min(MAX_RTO);
+            now += timeout;
+        }
+        tcb.poll(now, &mut out);
+        assert!(tcb.is_closed());
+        assert_eq!(tcb.error(), Some(Status::TimedOut));
+    }
+
+    #[test]
+    fn test_retransmits_data() {
+        let mut out = Vec::new();
+        let mut tcb = established(&mut out);
+        tcb.send(b"abc");
+        tcb.poll(100, &mut out);
+        out.clear();
+
+        let at = 100 + tcb.rto().timeout();
+        tcb.poll(at, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!((out[0].seq, out[0].payload.as_slice()), (ISS + 1, &b"abc"[..]));
+    }
+
+    #[test]
+    fn test_reorders_segments() {
+        let mut out = Vec::new();
+        let mut tcb = established(&mut out);
+        let base = PEER_ISS.wrapping_add(1);
+
+        tcb.on_segment(&peer(base.wrapping_add(5), ISS + 1, flags::ACK), b"world", 20, &mut out);
+        assert_eq!(tcb.readable(), 0);
+        assert_eq!(out.last().unwrap().ack, base);
+        out.clear();
+
+        tcb.on_segment(&peer(base, ISS + 1, flags::ACK), b"hello", 21, &mut out);
+        assert_eq!(out.last().unwrap().ack, base.wrapping_add(10));
+
+        // A duplicate is acknowledged but not delivered twice
+        tcb.on_segment(&peer(base, ISS + 1, flags::ACK), b"hello", 22, &mut out);
+        let mut buf = [0u8; 32];
+        let n = tcb.recv(&mut buf);
+        assert_eq!(&buf[..n], b"helloworld");
+    }
+
+    #[test]
+    fn test_close_handshake() {
+        let mut out = Vec::new();
+        let mut tcb = established(&mut out);
+        let base = PEER_ISS.wrapping_add(1);
+
+        tcb.shutdown();
+        tcb.poll(100, &mut out);
+        assert_eq!(out[0].flags, flags::FIN | flags::ACK);
+        assert_eq!(tcb.state(), State::FinWait1);
+        out.clear();
+
+        tcb.on_segment(&peer(base, ISS + 2, flags::ACK), &[], 110, &mut out);
+        assert_eq!(tcb.state(), State::FinWait2);
+
+        tcb.on_segment(&peer(base, ISS + 2, flags::FIN | flags::ACK), &[], 120, &mut out);
+        assert_eq!(tcb.state(), State::TimeWait);
+        assert!(tcb.at_eof());
+        assert_eq!(out.last().unwrap().ack, base.wrapping_add(1));
+
+        tcb.poll(120 + 2 * MSL - 1, &mut out);
+        assert_eq!(tcb.state(), State::TimeWait);
+        tcb.poll(120 + 2 * MSL, &mut out);
+        assert!(tcb.is_closed());
+        assert_eq!(tcb.error(), None);
+    }
+
+    #[test]
+    fn test_reset_and_refusal() {
+        let mut out = Vec::new();
+        let mut tcb = Tcb::connect(local(), remote(), ISS, MSS, 0, &mut out);
+        tcb.on_segment(&peer(0, ISS + 1, flags::RST | flags::ACK), &[], 5, &mut out);
+        assert_eq!(tcb.error(), Some(Status::NotFound));
+
+        let mut tcb = established(&mut out);
+        tcb.on_segment(&peer(PEER_ISS.wrapping_add(1), 0, flags::RST), &[], 20, &mut out);
+        assert!(tcb.is_closed());
+        assert_eq!(tcb.error(), Some(Status::HandleClosed));
+
+        let syn = peer(7, 0, flags::SYN);
+        let reset = Segment::reset(&syn, 0);
+        assert_eq!((reset.seq, reset.ack, reset.flags), (0, 8, flags::RST | flags::ACK));
+    }
+
+    #[test]
+    fn test_rto_estimate() {
+        let mut rto = Rto::new();
+        assert_eq!(rto.timeout(), INITIAL_RTO);
+        rto.sample(100);
+        assert_eq!(rto.timeout(), 300);
+        rto.sample(100);
+        assert_eq!(rto.timeout(), 248);
+        rto.backoff();
+        assert_eq!(rto.timeout(), 496);
+
+        // Fast links are held at the floor
+        rto.sample(1);
+        for _ in 0..20 {
+            rto.sample(1);
+        }
+        assert_eq!(rto.timeout(), MIN_RTO);
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TCP Segments
+//!
+//! Header encoding and sequence-number arithmetic. The only option
+//! understood is MSS, which is sent on SYN segments; others are skipped.
+//! Connection state lives in `tcb`.
+
+use core::net::Ipv4Addr;
+
+use crate::checksum::Checksum;
+use crate::ipv4::protocol;
+
+/// Size of a header without options
+pub const HEADER_SIZE: usize = 20;
+
+/// Size of a header carrying the MSS option
+pub const SYN_HEADER_SIZE: usize = 24;
+
+/// MSS assumed when the peer sends none (RFC 1122)
+pub const DEFAULT_MSS: u16 = 536;
+
+/// Header flags
+pub mod flags {
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const PSH: u8 = 0x08;
+    pub const ACK: u8 = 0x10;
+}
+
+/// Option kinds
+const OPTION_END: u8 = 0;
+const OPTION_NOP: u8 = 1;
+const OPTION_MSS: u8 = 2;
+
+/// `a` precedes `b` in sequence space
+pub fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+/// `a` precedes or equals `b` in sequence space
+pub fn seq_le(a: u32, b: u32) -> bool {
+    a == b || seq_lt(a, b)
+}
+
+/// TCP header fields the stack uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpHeader {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub seq: u32,
+    pub ack: u32,
+    pub flags: u8,
+    pub window: u16,
+
+    /// Maximum segment size option
+    pub mss: Option<u16>,
+}
+
+impl TcpHeader {
+    /// Whether all of `flags` are set
+    pub fn has(&self, flags: u8) -> bool {
+        self.flags & flags == flags
+    }
+
+    /// Validate a segment carried from `src` to `dst`
+    pub fn parse(src: Ipv4Addr, dst: Ipv4Addr, segment: &[u8]) -> Option<(Self, &[u8])> {
+        if segment.len() < HEADER_SIZE {
+            return None;
+        }
+        let header_len = (segment[12] >> 4) as usize * 4;
+        if header_len < HEADER_SIZE || header_len > segment.len() {
+            return None;
+        }
+        let sum = Checksum::new()
+            .add_pseudo_header(src, dst, protocol::TCP, segment.len() as u16)
+            .add(segment)
+            .finish();
+        if sum != 0 {
+            return None;
+        }
+
+        let mut mss = None;
+        let mut options = &segment[HEADER_SIZE..header_len];
+        while let [kind, rest @ ..] = options {
+            match *kind {
+                OPTION_END => break,
+                OPTION_NOP => options = rest,
+                _ => {
+                    let len = *rest.first()? as usize;
+                    if len < 2 || len > options.len() {
+                        return None;
+                    }
+                    if *kind == OPTION_MSS && len == 4 {
+                        mss = Some(u16::from_be_bytes([options[2], options[3]]));
+                    }
+                    options = &options[len..];
+                }
+            }
+        }
+
+        let header = Self {
+            src_port: u16::from_be_bytes([segment[0], segment[1]]),
+            dst_port: u16::from_be_bytes([segment[2], segment[3]]),
+            seq: u32::from_be_bytes([segment[4], segment[5], segment[6], segment[7]]),
+            ack: u32::from_be_bytes([segment[8], segment[9], segment[10], segment[11]]),
+            flags: segment[13] & 0x3f,
+            window: u16::from_be_bytes([segment[14], segment[15]]),
+            mss,
+        };
+        Some((header, &segment[header_len..]))
+    }
+
+    /// Size of the header as written
+    pub fn header_len(&self) -> usize {
+        if self.mss.is_some() {
+            SYN_HEADER_SIZE
+        } else {
+            HEADER_SIZE
+        }
+    }
+
+    /// Write header and `payload` to `buf`, returning the segment size
+    pub fn write(&self, src: Ipv4Addr, dst: Ipv4Addr, payload: &[u8], buf: &mut [u8]) -> usize {
+        let header_len = self.header_len();
+        let len = header_len + payload.len();
+        buf[0..2].copy_from_slice(&self.src_port.to_be_bytes());
+        buf[2..4].copy_from_slice(&self.dst_port.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.seq.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.ack.to_be_bytes());
+        buf[12] = ((header_len / 4) as u8) << 4;
+        buf[13] = self.flags;
+        buf[14..16].copy_from_slice(&self.window.to_be_bytes());
+        buf[16..20].fill(0);
+        if let Some(mss) = self.mss {
+            buf[20] = OPTION_MSS;
+            buf[21] = 4;
+            buf[22..24].copy_from_slice(&mss.to_be_bytes());
+        }
+        buf[header_len..len].copy_from_slice(payload);
+
+        let sum = Checksum::new()
+            .add_pseudo_header(src, dst, protocol::TCP, len as u16)
+            .add(&buf[..len])
+            .finish();
+        buf[16..18].copy_from_slice(&sum.to_be_bytes());
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_roundtrip() {
+        let src = Ipv4Addr::new(10, 0, 2, 15);
+        let dst = Ipv4Addr::new(10, 0, 2, 2);
+        let header = TcpHeader {
+            src_port: 49152,
+            dst_port: 80,
+            seq: 0xfffffff0,
+            ack: 0,
+            flags: flags::SYN,
+            window: 16384,
+            mss: Some(1460),
+        };
+        let mut buf = [0u8; 64];
+        let len = header.write(src, dst, b"", &mut buf);
+        assert_eq!(len, SYN_HEADER_SIZE);
+
+        let (parsed, payload) = TcpHeader::parse(src, dst, &buf[..len]).unwrap();
+        assert_eq!(parsed, header);
+        assert!(payload.is_empty());
+
+        let data = TcpHeader { flags: flags::ACK | flags::PSH, mss: None, ..header };
+        let len = data.write(src, dst, b"GET /", &mut buf);
+        let (parsed, payload) = TcpHeader::parse(src, dst, &buf[..len]).unwrap();
+        assert!(parsed.has(flags::ACK) && !parsed.has(flags::SYN));
+        assert_eq!(payload, b"GET /");
+
+        buf[len - 1] ^= 1;
+        assert!(TcpHeader::parse(src, dst, &buf[..len]).is_none());
+    }
+
+    #[test]
+    fn test_sequence_wraparound() {
+        assert!(seq_lt(0xffff_fff0, 0x10));
+        assert!(!seq_lt(0x10, 0xffff_fff0));
+        assert!(seq_le(5, 5));
+        assert!(!seq_lt(5, 5));
+    }
+}
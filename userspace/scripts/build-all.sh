@@ -51,6 +51,11 @@ echo "Building libipc..."
 cd "$USERSPACE_DIR/libipc"
 cargo build --release --target "$RUST_TARGET" || cargo build --release
 
+# Build libddk
+echo "Building libddk..."
+cd "$USERSPACE_DIR/libddk"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
 # Build librt
 echo "Building librt..."
 cd "$USERSPACE_DIR/librt"
@@ -71,6 +76,124 @@ echo "Building test programs..."
 cd "$USERSPACE_DIR/tests/hello"
 cargo build --release --target "$RUST_TARGET" || cargo build --release
 
+# Build drivers
+echo "Building virtio drivers..."
+cd "$USERSPACE_DIR/drivers/virtio"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building storage drivers..."
+cd "$USERSPACE_DIR/drivers/storage"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building USB drivers..."
+cd "$USERSPACE_DIR/drivers/usb"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building PS/2 drivers..."
+cd "$USERSPACE_DIR/drivers/ps2"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build input service
+echo "Building input service..."
+cd "$USERSPACE_DIR/input"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build filesystems
+echo "Building libvfs..."
+cd "$USERSPACE_DIR/libvfs"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building libbcache..."
+cd "$USERSPACE_DIR/libbcache"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building FAT filesystem..."
+cd "$USERSPACE_DIR/fs/fat"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building rxfs..."
+cd "$USERSPACE_DIR/fs/rxfs"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build block test tools
+echo "Building block tools..."
+cd "$USERSPACE_DIR/tests/blk"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build network stack and tools
+echo "Building netstack..."
+cd "$USERSPACE_DIR/netstack"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+echo "Building network tools..."
+cd "$USERSPACE_DIR/tests/net"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build syscall error path suite
+echo "Building core-tests..."
+cd "$USERSPACE_DIR/tests/core-tests"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build kernel counter tool
+echo "Building kcounter..."
+cd "$USERSPACE_DIR/tests/kcounter"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build kernel trace tool
+echo "Building ktrace..."
+cd "$USERSPACE_DIR/tests/ktrace"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build sampling profiler tool
+echo "Building prof..."
+cd "$USERSPACE_DIR/tests/prof"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build performance counter tool
+echo "Building perf..."
+cd "$USERSPACE_DIR/tests/perf"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build instruction trace tool
+echo "Building itrace..."
+cd "$USERSPACE_DIR/tests/itrace"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build EFI variable tool
+echo "Building efivar..."
+cd "$USERSPACE_DIR/tests/efivar"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build IPC benchmark
+echo "Building ipc-bench..."
+cd "$USERSPACE_DIR/tests/ipc-bench"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build condvar stress test
+echo "Building condvar-stress..."
+cd "$USERSPACE_DIR/tests/condvar-stress"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build driver host
+echo "Building devhost..."
+cd "$USERSPACE_DIR/devhost"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build crash reporter
+echo "Building crashsvc..."
+cd "$USERSPACE_DIR/crashsvc"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build debugger
+echo "Building dbg..."
+cd "$USERSPACE_DIR/dbg"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
+# Build framebuffer console
+echo "Building console..."
+cd "$USERSPACE_DIR/console"
+cargo build --release --target "$RUST_TARGET" || cargo build --release
+
 echo "Build complete!"
 
 # Create rootfs
@@ -81,12 +204,34 @@ mkdir -p "$ROOTFS_DIR/bin"
 # Copy libraries
 cp "$USERSPACE_DIR/libsys/target/release/libsys.a" "$ROOTFS_DIR/lib/"
 cp "$USERSPACE_DIR/libipc/target/release/libipc.a" "$ROOTFS_DIR/lib/"
+cp "$USERSPACE_DIR/libddk/target/release/libddk.a" "$ROOTFS_DIR/lib/"
 cp "$USERSPACE_DIR/librt/target/release/librt.a" "$ROOTFS_DIR/lib/"
 cp "$USERSPACE_DIR/libc-rx/target/release/libc.a" "$ROOTFS_DIR/lib/"
 cp "$USERSPACE_DIR/crt/target/release/libcrt0.a" "$ROOTFS_DIR/lib/"
 
 # Copy test programs
 cp "$USERSPACE_DIR/tests/hello/target/release/hello" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/core-tests/target/release/core-tests" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/devhost/target/release/devhost" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/crashsvc/target/release/crashsvc" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/dbg/target/release/dbg" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/console/target/release/console" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/blk/target/release/blkinfo" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/blk/target/release/blkcat" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/blk/target/release/diskhealth" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/blk/target/release/fatfs" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/blk/target/release/rxfsutil" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/net/target/release/ping" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/net/target/release/udpecho" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/net/target/release/tcpecho" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/kcounter/target/release/kcounter" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/ktrace/target/release/ktrace" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/prof/target/release/prof" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/perf/target/release/perf" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/itrace/target/release/itrace" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/efivar/target/release/efivar" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/ipc-bench/target/release/ipc-bench" "$ROOTFS_DIR/bin/"
+cp "$USERSPACE_DIR/tests/condvar-stress/target/release/condvar-stress" "$ROOTFS_DIR/bin/"
 
 echo "Rootfs created at $ROOTFS_DIR"
 echo "Done!"
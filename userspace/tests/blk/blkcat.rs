@@ -0,0 +1,99 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! blkcat - Dump Disk Blocks
+//!
+//! Reads blocks from the first disk, or one of its partitions, over the
+//! block protocol and prints them as a hex dump.
+//!
+//! Usage: `blkcat [instance] [lba] [count]`
+//!
+//! `instance` is 0 for the whole disk or a partition number; `lba` and
+//! `count` default to 0 and 1.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libsys;
+extern crate storage;
+extern crate virtio;
+
+mod blkdev;
+
+use alloc::vec;
+use core::fmt::Write;
+
+use libddk::*;
+
+use blkdev::{args, parse_u64, Disk, StdoutWriter};
+
+/// Largest dump, in blocks
+const MAX_BLOCKS: u64 = 64;
+
+/// Print `data` as offset, hex and ASCII columns
+fn hexdump(writer: &mut StdoutWriter, base: u64, data: &[u8]) {
+    for (i, line) in data.chunks(16).enumerate() {
+        let _ = write!(writer, "{:08x}  ", base + (i * 16) as u64);
+        for byte in line {
+            let _ = write!(writer, "{:02x} ", byte);
+        }
+        let _ = write!(writer, " |");
+        for &byte in line {
+            let c = if (0x20..0x7f).contains(&byte) { byte as char } else { '.' };
+            let _ = write!(writer, "{}", c);
+        }
+        let _ = writeln!(writer, "|");
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let args = args(argc, argv);
+    let mut values = [0u64, 0, 1];
+    for (value, arg) in values.iter_mut().zip(args.iter()) {
+        match parse_u64(arg) {
+            Some(v) => *value = v,
+            None => {
+                let _ = writeln!(writer, "usage: blkcat [instance] [lba] [count]");
+                return 2;
+            }
+        }
+    }
+    let [instance, lba, count] = values;
+    if count == 0 || count > MAX_BLOCKS || instance > u32::MAX as u64 {
+        let _ = writeln!(writer, "blkcat: count must be 1..={}", MAX_BLOCKS);
+        return 2;
+    }
+
+    let mut client = match Disk::first().and_then(|disk| disk.open(instance as u32)) {
+        Ok(client) => client,
+        Err(e) => {
+            let _ = writeln!(writer, "blkcat: open failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    let block_size = client.info().block_size as u64;
+    let mut buf = vec![0u8; (count * block_size) as usize];
+    if let Err(e) = client.read_blocks(lba, &mut buf) {
+        let _ = writeln!(writer, "blkcat: read failed: {:?}", e);
+        return 1;
+    }
+    hexdump(&mut writer, lba * block_size, &buf);
+
+    0
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
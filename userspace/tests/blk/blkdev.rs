@@ -0,0 +1,110 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Shared setup for the block test tools
+//!
+//! There is no device namespace to look disks up in yet, so each tool
+//! binds the first disk controller itself (virtio-blk, NVMe or AHCI)
+//! through an in-process driver host and talks to it over the block
+//! protocol. Run the tools instead of `devhost`, not alongside it.
+
+// Not every tool uses every helper
+#![allow(dead_code)]
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use libddk::*;
+use libsys::*;
+
+/// Simple stdout writer
+pub struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Drivers the tools can bind
+static DRIVERS: [DriverEntry; 3] = [virtio::blk::DRIVER, storage::nvme::DRIVER, storage::ahci::DRIVER];
+
+/// Command-line arguments after the program name
+pub fn args(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    let mut args = Vec::new();
+    for i in 1..argc.max(0) as isize {
+        unsafe {
+            let arg = *argv.offset(i);
+            if arg.is_null() {
+                break;
+            }
+            let mut len = 0;
+            while *arg.add(len) != 0 {
+                len += 1;
+            }
+            if let Ok(s) = core::str::from_utf8(core::slice::from_raw_parts(arg, len)) {
+                args.push(s);
+            }
+        }
+    }
+    args
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number
+pub fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// A disk bound in this process
+pub struct Disk {
+    local: Rc<RefCell<LocalDevice>>,
+}
+
+impl Disk {
+    /// Bind the first disk controller on the PCI bus
+    pub fn first() -> Result<Self> {
+        let root = startup::root_resource()?;
+
+        let mut index = 0;
+        loop {
+            let device = PciDevice::get_nth(&root, index)?;
+            index += 1;
+            let desc = device.info().descriptor();
+            if host::find_driver(&DRIVERS, &desc).is_some() {
+                let local = LocalDevice::bind(&DRIVERS, desc, device.into_handle())?;
+                return Ok(Self {
+                    local: Rc::new(RefCell::new(local)),
+                });
+            }
+        }
+    }
+
+    /// Connect to instance `instance` (0 = whole disk, N = partition N)
+    ///
+    /// Controllers with several disks number the next disk's instances
+    /// after the last partition of the one before.
+    ///
+    /// The client polls the in-process host whenever it waits.
+    pub fn open(&self, instance: u32) -> Result<BlockClient> {
+        let (body, handles) = self.local.borrow_mut().open(instance)?;
+        let mut client = BlockClient::new(&body, handles)?;
+        let local = self.local.clone();
+        client.set_idle(Box::new(move || {
+            let _ = local.borrow_mut().host_mut().poll();
+        }));
+        Ok(client)
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! blkinfo - Describe a Disk
+//!
+//! Prints the geometry of the first disk and its GPT, then
+//! opens each published partition instance to check that its geometry
+//! matches the table.
+//!
+//! Usage: `blkinfo`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libsys;
+extern crate storage;
+extern crate virtio;
+
+mod blkdev;
+
+use core::fmt::Write;
+
+use libddk::gpt;
+use libddk::*;
+
+use blkdev::{Disk, StdoutWriter};
+
+#[no_mangle]
+pub extern "C" fn main(_argc: i32, _argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let disk = match Disk::first() {
+        Ok(disk) => disk,
+        Err(e) => {
+            let _ = writeln!(writer, "blkinfo: no disk: {:?}", e);
+            return 1;
+        }
+    };
+    let mut whole = match disk.open(0) {
+        Ok(client) => client,
+        Err(e) => {
+            let _ = writeln!(writer, "blkinfo: open failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    let info = whole.info();
+    let _ = writeln!(
+        writer,
+        "disk: {} blocks of {} bytes ({} MiB), max transfer {}",
+        info.block_count,
+        info.block_size,
+        info.size() >> 20,
+        info.max_transfer
+    );
+
+    let table = match gpt::read_gpt(&mut whole) {
+        Ok(table) => table,
+        Err(e) => {
+            let _ = writeln!(writer, "gpt: none ({:?})", e);
+            return 0;
+        }
+    };
+    let _ = writeln!(
+        writer,
+        "gpt: disk {}, usable {}..={}",
+        table.header.disk_guid, table.header.first_usable_lba, table.header.last_usable_lba
+    );
+
+    let mut status = 0;
+    for (i, part) in table.partitions.iter().enumerate() {
+        let _ = writeln!(
+            writer,
+            "  {:>3}  {:>10}..={:<10} {:>8} KiB  {}  {}",
+            part.index,
+            part.first_lba,
+            part.last_lba,
+            (part.block_count() * info.block_size as u64) >> 10,
+            part.type_guid.type_name().unwrap_or("unknown"),
+            part.name
+        );
+
+        // The stack publishes partitions in table order
+        let instance = i as u32 + 1;
+        match disk.open(instance) {
+            Ok(client) if client.info().block_count == part.block_count() => {}
+            Ok(client) => {
+                let _ = writeln!(
+                    writer,
+                    "blkinfo: instance {} has {} blocks, expected {}",
+                    instance,
+                    client.info().block_count,
+                    part.block_count()
+                );
+                status = 1;
+            }
+            Err(e) => {
+                let _ = writeln!(writer, "blkinfo: instance {} open failed: {:?}", instance, e);
+                status = 1;
+            }
+        }
+    }
+
+    status
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
@@ -0,0 +1,145 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! diskhealth - Show a Disk's Identity and Health
+//!
+//! Asks the first disk for the identity and health it reports about
+//! itself: NVMe drives through the SMART / Health log, ATA drives through
+//! SMART. Values the drive doesn't report are left out; virtio disks
+//! report nothing.
+//!
+//! Usage: `diskhealth [instance]`
+//!
+//! Exits with 2 if the drive reports a warning.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libsys;
+extern crate storage;
+extern crate virtio;
+
+mod blkdev;
+
+use core::fmt::Write;
+
+use libddk::block::health_warning;
+use libddk::*;
+
+use blkdev::{args, parse_u64, Disk, StdoutWriter};
+
+/// Names of the `health_warning` bits
+const WARNINGS: [(u8, &str); 5] = [
+    (health_warning::SPARE, "spare low"),
+    (health_warning::TEMPERATURE, "temperature"),
+    (health_warning::RELIABILITY, "reliability degraded"),
+    (health_warning::READ_ONLY, "read-only"),
+    (health_warning::BACKUP, "backup failed"),
+];
+
+fn print_counter(writer: &mut StdoutWriter, name: &str, value: Option<u64>) {
+    if let Some(value) = value {
+        let _ = writeln!(writer, "{:<20}{}", name, value);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+    let args = args(argc, argv);
+    let instance = match args.first().map(|s| parse_u64(s)) {
+        None => 0,
+        Some(Some(n)) => n as u32,
+        Some(None) => {
+            let _ = writeln!(writer, "usage: diskhealth [instance]");
+            return 1;
+        }
+    };
+
+    let disk = match Disk::first() {
+        Ok(disk) => disk,
+        Err(e) => {
+            let _ = writeln!(writer, "diskhealth: no disk: {:?}", e);
+            return 1;
+        }
+    };
+    let mut client = match disk.open(instance) {
+        Ok(client) => client,
+        Err(e) => {
+            let _ = writeln!(writer, "diskhealth: open failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    let info = client.info();
+    let health = match client.health() {
+        Ok(health) => health,
+        Err(e) => {
+            let _ = writeln!(writer, "diskhealth: no health information: {:?}", e);
+            return 1;
+        }
+    };
+
+    let _ = writeln!(writer, "{:<20}{}", "model", health.model_name());
+    let _ = writeln!(writer, "{:<20}{}", "serial", health.serial_number());
+    let _ = writeln!(writer, "{:<20}{}", "firmware", health.firmware_revision());
+    let _ = writeln!(
+        writer,
+        "{:<20}{} blocks of {} bytes ({} MiB)",
+        "capacity",
+        info.block_count,
+        info.block_size,
+        info.size() >> 20
+    );
+
+    if health.healthy() {
+        let _ = writeln!(writer, "{:<20}ok", "status");
+    } else {
+        let _ = write!(writer, "{:<20}WARNING:", "status");
+        for (bit, name) in WARNINGS {
+            if health.warnings & bit != 0 {
+                let _ = write!(writer, " {}", name);
+            }
+        }
+        let _ = writeln!(writer);
+    }
+
+    if let Some(celsius) = health.temperature {
+        let _ = writeln!(writer, "{:<20}{} C", "temperature", celsius);
+    }
+    if let Some(percent) = health.available_spare {
+        let _ = writeln!(writer, "{:<20}{}%", "available spare", percent);
+    }
+    if let Some(percent) = health.percentage_used {
+        let _ = writeln!(writer, "{:<20}{}%", "endurance used", percent);
+    }
+    print_counter(&mut writer, "power-on hours", health.power_on_hours);
+    print_counter(&mut writer, "power cycles", health.power_cycles);
+    print_counter(&mut writer, "unsafe shutdowns", health.unsafe_shutdowns);
+    print_counter(&mut writer, "media errors", health.media_errors);
+    print_counter(&mut writer, "reallocated sectors", health.reallocated_sectors);
+    if let Some(bytes) = health.bytes_read {
+        let _ = writeln!(writer, "{:<20}{} MiB", "data read", bytes >> 20);
+    }
+    if let Some(bytes) = health.bytes_written {
+        let _ = writeln!(writer, "{:<20}{} MiB", "data written", bytes >> 20);
+    }
+
+    if health.healthy() {
+        0
+    } else {
+        2
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
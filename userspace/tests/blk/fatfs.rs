@@ -0,0 +1,243 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! fatfs - FAT32 Volume Tool
+//!
+//! Mounts the FAT32 volume on a partition of the first disk, usually the
+//! EFI system partition, and works with it through the Vfs protocol. The
+//! filesystem server runs on a thread of its own, talking to this one
+//! over a channel.
+//!
+//! Usage: `fatfs <instance> <command> [args]`
+//!
+//! Commands:
+//! - `info` - filesystem type, label and space
+//! - `ls [path]` - list a directory
+//! - `cat <path>` - print a file
+//! - `put <path> <text>` - write a line of text to a file
+//! - `mkdir <path>`, `rm <path>`, `mv <from> <to>`
+//! - `check` - look for inconsistencies; changes nothing
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate bcache;
+extern crate fat;
+extern crate ipc;
+extern crate libddk;
+extern crate libsys;
+extern crate rt;
+extern crate storage;
+extern crate virtio;
+extern crate vfs;
+
+mod blkdev;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use bcache::{BlockCache, CacheConfig};
+use fat::{FatServer, Volume};
+use ipc::Channel;
+use libddk::*;
+use libsys::Result;
+use rt::Thread;
+use vfs::{node_kind, open_flags, VfsProxy, MAX_TRANSFER};
+
+use blkdev::{args, parse_u64, Disk, StdoutWriter};
+
+const USAGE: &str = "usage: fatfs <instance> info|ls|cat|put|mkdir|rm|mv|check [args]";
+
+/// The partition, behind a block cache
+type CachedDisk = BlockCache<BlockClient>;
+
+extern "C" fn server_main(arg: *mut u8) {
+    let server = unsafe { Box::from_raw(arg as *mut (FatServer<CachedDisk>, Channel)) };
+    let (server, channel) = *server;
+    let _ = server.run(&channel);
+}
+
+/// Start a server for `volume`, returning a proxy to it
+fn serve(volume: Volume<CachedDisk>) -> Result<VfsProxy> {
+    let (client, server) = Channel::create()?;
+    let arg = Box::new((FatServer::new(volume), server));
+    Thread::spawn(server_main, Box::into_raw(arg) as *mut u8)?.detach();
+    Ok(VfsProxy::new(client))
+}
+
+fn info(writer: &mut StdoutWriter, fs: &mut VfsProxy) -> Result<()> {
+    let info = fs.query()?;
+    let name = core::str::from_utf8(&info.name).unwrap_or("?");
+    let label = core::str::from_utf8(&info.label).unwrap_or("?");
+    let _ = writeln!(writer, "type:       {}", name);
+    let _ = writeln!(writer, "label:      {}", if label.is_empty() { "(none)" } else { label });
+    let _ = writeln!(writer, "cluster:    {} bytes", info.block_size);
+    let _ = writeln!(writer, "size:       {} KiB", info.total_bytes / 1024);
+    let _ = writeln!(writer, "free:       {} KiB", info.free_bytes / 1024);
+    Ok(())
+}
+
+fn ls(writer: &mut StdoutWriter, fs: &mut VfsProxy, path: &str) -> Result<()> {
+    let dir = fs.open(path.as_bytes().to_vec(), open_flags::READ | open_flags::DIRECTORY)?;
+    let mut cookie = 0;
+    loop {
+        let entries = fs.read_dir(dir, cookie)?;
+        let Some(last) = entries.last() else {
+            break;
+        };
+        cookie = last.cookie;
+        for entry in &entries {
+            let name = core::str::from_utf8(&entry.name).unwrap_or("?");
+            match entry.kind {
+                node_kind::DIRECTORY => {
+                    let _ = writeln!(writer, "{:>10}  {}/", "", name);
+                }
+                _ => {
+                    let _ = writeln!(writer, "{:>10}  {}", entry.size, name);
+                }
+            }
+        }
+    }
+    fs.close(dir)
+}
+
+fn cat(writer: &mut StdoutWriter, fs: &mut VfsProxy, path: &str) -> Result<()> {
+    let file = fs.open(path.as_bytes().to_vec(), open_flags::READ)?;
+    let mut offset = 0;
+    loop {
+        let data = fs.read(file, offset, MAX_TRANSFER)?;
+        if data.is_empty() {
+            break;
+        }
+        offset += data.len() as u64;
+        for chunk in data.utf8_chunks() {
+            let _ = write!(writer, "{}", chunk.valid());
+            if !chunk.invalid().is_empty() {
+                let _ = write!(writer, "\u{FFFD}");
+            }
+        }
+    }
+    fs.close(file)
+}
+
+fn put(fs: &mut VfsProxy, path: &str, text: &[&str]) -> Result<()> {
+    let flags = open_flags::WRITE | open_flags::CREATE | open_flags::TRUNCATE;
+    let file = fs.open(path.as_bytes().to_vec(), flags)?;
+    let mut line = Vec::new();
+    for (i, word) in text.iter().enumerate() {
+        if i > 0 {
+            line.push(b' ');
+        }
+        line.extend_from_slice(word.as_bytes());
+    }
+    line.push(b'\n');
+    fs.write(file, 0, line)?;
+    fs.close(file)
+}
+
+fn check(writer: &mut StdoutWriter, mut volume: Volume<CachedDisk>) -> Result<bool> {
+    let report = fat::check(&mut volume)?;
+    for problem in &report.problems {
+        let _ = writeln!(writer, "{}", problem);
+    }
+    let _ = writeln!(
+        writer,
+        "{} files, {} directories, {} clusters used, {} free",
+        report.files, report.directories, report.used_clusters, report.free_clusters
+    );
+    let stats = volume.device_mut().stats();
+    let _ = writeln!(
+        writer,
+        "cache: {} hits, {} misses, {} read ahead",
+        stats.hits, stats.misses, stats.read_ahead
+    );
+    let _ = writeln!(writer, "{}", if report.is_clean() { "clean" } else { "PROBLEMS FOUND" });
+    Ok(report.is_clean())
+}
+
+/// Whether `command` takes `rest` as its arguments
+fn known(command: &str, rest: &[&str]) -> bool {
+    match command {
+        "info" | "check" => rest.is_empty(),
+        "ls" => rest.len() <= 1,
+        "cat" | "mkdir" | "rm" => rest.len() == 1,
+        "mv" => rest.len() == 2,
+        "put" => !rest.is_empty(),
+        _ => false,
+    }
+}
+
+fn run(writer: &mut StdoutWriter, volume: Volume<CachedDisk>, command: &str, rest: &[&str]) -> Result<bool> {
+    if command == "check" {
+        return check(writer, volume);
+    }
+
+    let mut fs = serve(volume)?;
+    match (command, rest) {
+        ("info", []) => info(writer, &mut fs)?,
+        ("ls", []) => ls(writer, &mut fs, "/")?,
+        ("ls", [path]) => ls(writer, &mut fs, path)?,
+        ("cat", [path]) => cat(writer, &mut fs, path)?,
+        ("put", [path, text @ ..]) => put(&mut fs, path, text)?,
+        ("mkdir", [path]) => fs.mkdir(path.as_bytes().to_vec())?,
+        ("rm", [path]) => fs.unlink(path.as_bytes().to_vec())?,
+        ("mv", [from, to]) => fs.rename(from.as_bytes().to_vec(), to.as_bytes().to_vec())?,
+        _ => unreachable!(),
+    }
+    // The process may exit before the server notices the channel closing
+    fs.sync()?;
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let args = args(argc, argv);
+    let (instance, command, rest) = match args.as_slice() {
+        [instance, command, rest @ ..] => match parse_u64(instance) {
+            Some(instance) if instance <= u32::MAX as u64 && known(command, rest) => (instance as u32, *command, rest),
+            _ => {
+                let _ = writeln!(writer, "{}", USAGE);
+                return 2;
+            }
+        },
+        _ => {
+            let _ = writeln!(writer, "{}", USAGE);
+            return 2;
+        }
+    };
+
+    let volume = match Disk::first()
+        .and_then(|disk| disk.open(instance))
+        .and_then(|disk| BlockCache::new(disk, CacheConfig::default()))
+        .and_then(Volume::mount)
+    {
+        Ok(volume) => volume,
+        Err(e) => {
+            let _ = writeln!(writer, "fatfs: mount failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&mut writer, volume, command, rest) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            let _ = writeln!(writer, "fatfs: {} failed: {:?}", command, e);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
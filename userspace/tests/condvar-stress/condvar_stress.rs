@@ -0,0 +1,232 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! condvar-stress - Stress librt Condvar
+//!
+//! Runs many threads against one [`rt::Condvar`]:
+//!
+//! - `broadcast`: waiters park on a flag and one `notify_all` must wake
+//!   every one of them
+//! - `handoff`: consumers take items a producer hands out with
+//!   `notify_one`; a lost wake-up leaves an item untaken and hangs the run
+//! - `timeout`: `wait_timeout` with no notifier times out, and not early
+//!
+//! Usage: `condvar-stress [rounds]`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libsys;
+extern crate rt;
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use libsys::*;
+use rt::timer::get_monotonic_time;
+use rt::{Condvar, Mutex, Thread, WaitResult};
+
+/// Rounds of every test by default
+const DEFAULT_ROUNDS: u64 = 100;
+
+/// Threads parked for each broadcast
+const WAITERS: u64 = 32;
+
+/// Consumer threads in the handoff test
+const CONSUMERS: u64 = 8;
+
+/// Items handed out per handoff round
+const ITEMS: u64 = 1000;
+
+/// Timeout used by the timeout test (10 ms)
+const TIMEOUT_NS: u64 = 10_000_000;
+
+const USAGE: &str = "usage: condvar-stress [rounds]";
+
+/// Lock guarding the shared state below
+static LOCK: Mutex = Mutex::new();
+
+/// Signaled when `GO`, `ITEMS_READY` or `DONE` change
+static CHANGED: Condvar = Condvar::new();
+
+/// Signaled when a broadcast waiter has parked
+static PARKED_CV: Condvar = Condvar::new();
+
+// Shared state, only changed with `LOCK` held
+static GO: AtomicBool = AtomicBool::new(false);
+static PARKED: AtomicU64 = AtomicU64::new(0);
+static AWAKE: AtomicU64 = AtomicU64::new(0);
+static ITEMS_READY: AtomicU64 = AtomicU64::new(0);
+static TAKEN: AtomicU64 = AtomicU64::new(0);
+static DONE: AtomicBool = AtomicBool::new(false);
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Park until `GO` is set, then count ourselves awake
+extern "C" fn broadcast_waiter(_arg: *mut u8) {
+    let guard = LOCK.lock();
+    PARKED.fetch_add(1, Ordering::Relaxed);
+    PARKED_CV.notify_one();
+
+    let _guard = CHANGED.wait_while(guard, || !GO.load(Ordering::Relaxed));
+    AWAKE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Take items until the producer is done and none are left
+extern "C" fn handoff_consumer(_arg: *mut u8) {
+    loop {
+        let guard = LOCK.lock();
+        let _guard = CHANGED.wait_while(guard, || {
+            ITEMS_READY.load(Ordering::Relaxed) == 0 && !DONE.load(Ordering::Relaxed)
+        });
+        if ITEMS_READY.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        ITEMS_READY.fetch_sub(1, Ordering::Relaxed);
+        TAKEN.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Spawn `count` threads running `func`
+fn spawn_all(count: u64, func: extern "C" fn(*mut u8)) -> Result<Vec<Thread>> {
+    (0..count).map(|_| Thread::spawn(func, core::ptr::null_mut())).collect()
+}
+
+/// Join every thread in `threads`
+fn join_all(threads: Vec<Thread>) -> Result<()> {
+    threads.into_iter().try_for_each(Thread::join)
+}
+
+/// One `notify_all` wakes every parked waiter
+fn broadcast() -> Result<bool> {
+    GO.store(false, Ordering::Relaxed);
+    PARKED.store(0, Ordering::Relaxed);
+    AWAKE.store(0, Ordering::Relaxed);
+
+    let threads = spawn_all(WAITERS, broadcast_waiter)?;
+
+    {
+        let guard = LOCK.lock();
+        let _guard = PARKED_CV.wait_while(guard, || PARKED.load(Ordering::Relaxed) < WAITERS);
+        GO.store(true, Ordering::Relaxed);
+        CHANGED.notify_all();
+    }
+
+    join_all(threads)?;
+    Ok(AWAKE.load(Ordering::Relaxed) == WAITERS)
+}
+
+/// Every item handed out with `notify_one` is taken
+fn handoff() -> Result<bool> {
+    ITEMS_READY.store(0, Ordering::Relaxed);
+    TAKEN.store(0, Ordering::Relaxed);
+    DONE.store(false, Ordering::Relaxed);
+
+    let threads = spawn_all(CONSUMERS, handoff_consumer)?;
+
+    for _ in 0..ITEMS {
+        let _guard = LOCK.lock();
+        ITEMS_READY.fetch_add(1, Ordering::Relaxed);
+        CHANGED.notify_one();
+    }
+    {
+        let _guard = LOCK.lock();
+        DONE.store(true, Ordering::Relaxed);
+        CHANGED.notify_all();
+    }
+
+    join_all(threads)?;
+    Ok(TAKEN.load(Ordering::Relaxed) == ITEMS)
+}
+
+/// `wait_timeout` with no notifier times out after the full timeout
+fn timeout() -> bool {
+    let condvar = Condvar::new();
+    let start = get_monotonic_time();
+    let (_guard, result) = condvar.wait_timeout(LOCK.lock(), TIMEOUT_NS);
+    result == WaitResult::TimedOut && get_monotonic_time() - start >= TIMEOUT_NS
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let rounds = match (argc > 1).then(|| unsafe { arg(argv, 1) }).flatten() {
+        None => DEFAULT_ROUNDS,
+        Some(s) => match s.parse::<u64>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                let _ = writeln!(writer, "{}", USAGE);
+                return 1;
+            }
+        },
+    };
+
+    let tests: [(&str, fn() -> Result<bool>); 3] = [
+        ("broadcast", broadcast),
+        ("handoff", handoff),
+        ("timeout", || Ok(timeout())),
+    ];
+
+    let mut failed = 0;
+    for (name, test) in tests.iter() {
+        let mut passed = 0;
+        for _ in 0..rounds {
+            match test() {
+                Ok(true) => passed += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    let _ = writeln!(writer, "condvar-stress: {}: {:?}", name, e);
+                    break;
+                }
+            }
+        }
+        let _ = writeln!(writer, "{:>10}: {}/{} rounds passed", name, passed, rounds);
+        if passed != rounds {
+            failed += 1;
+        }
+    }
+
+    if failed == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
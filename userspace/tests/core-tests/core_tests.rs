@@ -0,0 +1,539 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! core-tests - Syscall Error Path Suite
+//!
+//! Calls every kernel syscall with arguments it must reject and checks the
+//! exact status that comes back: bad handles, missing rights, null,
+//! kernel-half and misaligned user pointers, zero-length buffers, deadlines
+//! already in the past and handle counts over the limit. A change to any of
+//! these codes is an ABI change and shows up here first.
+//!
+//! Prints one `PASS` or `FAIL` line per case and a summary, and exits with
+//! the number of failures. With a filter, runs only the cases whose name
+//! contains it.
+//!
+//! Pointers into unmapped user pages are not covered: user copies have no
+//! fault recovery yet, so the kernel would take the fault itself. Rights
+//! are checked through the resource syscalls, since object creation returns
+//! object IDs rather than installing handles the caller could reduce.
+//!
+//! Usage: `core-tests [filter]`
+
+#![no_std]
+#![no_main]
+
+extern crate libsys;
+extern crate rt;
+
+use core::fmt::Write;
+
+use libsys::syscall::{self, SyscallNumber};
+
+/// Kernel syscall numbers
+///
+/// Spelled out rather than taken from libsys's `SyscallNumber`, which is
+/// generated from the same table as the kernel, so that renumbering a
+/// frozen syscall in `abi/syscalls.abi` fails here.
+mod nr {
+    pub const PROCESS_CREATE: u64 = 0x01;
+    pub const PROCESS_START: u64 = 0x02;
+    pub const THREAD_CREATE: u64 = 0x03;
+    pub const THREAD_START: u64 = 0x04;
+    pub const HANDLE_CLOSE: u64 = 0x07;
+
+    pub const VMO_CREATE: u64 = 0x10;
+    pub const VMO_READ: u64 = 0x11;
+    pub const VMO_WRITE: u64 = 0x12;
+    pub const VMO_CLONE: u64 = 0x13;
+    pub const VMAR_MAP: u64 = 0x14;
+    pub const VMAR_UNMAP: u64 = 0x15;
+    pub const VMAR_PROTECT: u64 = 0x16;
+    pub const VMO_SET_SIZE: u64 = 0x17;
+    pub const VMO_OP_RANGE: u64 = 0x18;
+
+    pub const CHANNEL_CREATE: u64 = 0x20;
+    pub const CHANNEL_WRITE: u64 = 0x21;
+    pub const CHANNEL_READ: u64 = 0x22;
+    pub const CHANNEL_WRITE_ETC: u64 = 0x29;
+    pub const CHANNEL_READ_ETC: u64 = 0x2A;
+    pub const EVENTPAIR_CREATE: u64 = 0x24;
+    pub const OBJECT_SIGNAL: u64 = 0x25;
+    pub const OBJECT_WAIT_ONE: u64 = 0x26;
+    pub const OBJECT_WAIT_MANY: u64 = 0x27;
+    pub const OBJECT_SIGNAL_PEER: u64 = 0x2B;
+    pub const COUNTER_CREATE: u64 = 0x2C;
+    pub const COUNTER_ADD: u64 = 0x2D;
+    pub const COUNTER_READ: u64 = 0x2E;
+    pub const COUNTER_WRITE: u64 = 0x2F;
+
+    pub const JOB_CREATE: u64 = 0x30;
+    pub const HANDLE_DUPLICATE: u64 = 0x31;
+    pub const HANDLE_TRANSFER: u64 = 0x32;
+    pub const HANDLE_CLOSE_MANY: u64 = 0x35;
+    pub const OBJECT_GET_CHILD: u64 = 0x36;
+
+    pub const CLOCK_GET: u64 = 0x40;
+    pub const TIMER_CREATE: u64 = 0x41;
+    pub const TIMER_SET: u64 = 0x42;
+    pub const TIMER_CANCEL: u64 = 0x43;
+
+    pub const HYPERVISOR_CREATE: u64 = 0x90;
+    pub const HYPERVISOR_OP: u64 = 0x91;
+    pub const GUEST_SET_TRAP: u64 = 0x92;
+    pub const VCPU_CREATE: u64 = 0x93;
+    pub const VCPU_ENTER: u64 = 0x94;
+    pub const VCPU_KICK: u64 = 0x95;
+    pub const VCPU_INTERRUPT: u64 = 0x96;
+    pub const VCPU_READ_STATE: u64 = 0x97;
+    pub const VCPU_WRITE_STATE: u64 = 0x98;
+
+    pub const SYSTEM_GET_VERSION: u64 = 0xA0;
+    pub const SYSTEM_POWERCTL: u64 = 0xA2;
+    pub const CPRNG_DRAW: u64 = 0xA3;
+    pub const CPRNG_ADD_ENTROPY: u64 = 0xA4;
+    pub const CRASHLOG_READ: u64 = 0xA5;
+    pub const KCOUNTER_READ: u64 = 0xA6;
+    pub const KTRACE_READ: u64 = 0xA7;
+    pub const RESOURCE_CREATE: u64 = 0xAA;
+    pub const SYSTEM_GET_EVENT: u64 = 0xAB;
+    pub const SYSTEM_GET_FEATURES: u64 = 0xAC;
+
+    pub const VMO_SET_CACHE_POLICY: u64 = 0xDA;
+    pub const VMO_TRANSFER_DATA: u64 = 0xDB;
+
+    pub const FIFO_CREATE: u64 = 0xF0;
+    pub const FIFO_WRITE: u64 = 0xF1;
+    pub const FIFO_READ: u64 = 0xF2;
+
+    /// Inside no assigned range
+    pub const UNASSIGNED: u64 = 0x9F;
+}
+
+/// Kernel status codes, as returned in the syscall result register
+mod status {
+    pub const OK: i64 = 0;
+    pub const ERR_INVALID_ARGS: i64 = -2;
+    pub const ERR_BAD_HANDLE: i64 = -3;
+    pub const ERR_NOT_SUPPORTED: i64 = -5;
+    pub const ERR_TIMED_OUT: i64 = -7;
+    pub const ERR_NOT_FOUND: i64 = -8;
+    pub const ERR_ACCESS_DENIED: i64 = -10;
+    pub const ERR_BUFFER_TOO_SMALL: i64 = -16;
+    pub const ERR_OUT_OF_RANGE: i64 = -17;
+    pub const ERR_SHOULD_WAIT: i64 = -18;
+    pub const ERR_PEER_CLOSED: i64 = -20;
+}
+
+use status::*;
+
+/// A handle value no table hands out
+const BAD_HANDLE: u64 = 0xdead_beef;
+
+/// Start of the kernel half on every supported architecture
+const KERNEL_PTR: u64 = 0xffff_ff80_0000_0000;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Channel message and wait limits
+const MAX_MSG_SIZE: u64 = 64 * 1024;
+const MAX_MSG_HANDLES: u64 = 64;
+const MAX_WAIT_ITEMS: u64 = 16;
+
+/// First user signal bit
+const USER_0: u64 = 0x0100_0000;
+
+/// Asserted on a counter while its value is above zero
+const COUNTER_POSITIVE: u64 = 0x10;
+
+/// CPRNG per-call limit
+const MAX_CPRNG_LEN: u64 = 256;
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scratch memory for cases that need a real user buffer
+#[repr(C, align(16))]
+struct Scratch([u8; 256]);
+
+static mut SCRATCH: Scratch = Scratch([0; 256]);
+
+/// Address of the scratch buffer, zeroed, plus `offset`
+fn scratch(offset: usize) -> u64 {
+    unsafe {
+        let buf = &mut *core::ptr::addr_of_mut!(SCRATCH);
+        buf.0.fill(0);
+        buf.0.as_mut_ptr().add(offset) as u64
+    }
+}
+
+/// Read a `u64` the kernel stored at `addr`
+fn read_u64(addr: u64) -> u64 {
+    unsafe { core::ptr::read_unaligned(addr as *const u64) }
+}
+
+/// Runs cases and keeps the tally
+struct Suite {
+    out: StdoutWriter,
+    filter: Option<&'static str>,
+    passed: u32,
+    failed: u32,
+}
+
+impl Suite {
+    /// Issue syscall `n` with `args` and check it returns `expected`
+    ///
+    /// Returns the raw result so a case can use what it created.
+    fn check(&mut self, name: &str, n: u64, args: &[u64], expected: i64) -> i64 {
+        if let Some(filter) = self.filter {
+            if !name.contains(filter) {
+                return expected;
+            }
+        }
+
+        let mut a = [0u64; 6];
+        a[..args.len()].copy_from_slice(args);
+        let ret = unsafe { syscall::syscall6(n, a[0], a[1], a[2], a[3], a[4], a[5]) } as i64;
+
+        if ret == expected {
+            self.passed += 1;
+            let _ = writeln!(self.out, "PASS {}", name);
+        } else {
+            self.failed += 1;
+            let _ = writeln!(self.out, "FAIL {}: expected {} got {}", name, expected, ret);
+        }
+        ret
+    }
+}
+
+fn task(s: &mut Suite) {
+    let name = scratch(0);
+    s.check("process_create/name_too_long", nr::PROCESS_CREATE, &[0, name, 65, 0], ERR_INVALID_ARGS);
+    s.check("process_create/kernel_name", nr::PROCESS_CREATE, &[0, KERNEL_PTR, 4, 0], ERR_INVALID_ARGS);
+    s.check("process_start/bad_process", nr::PROCESS_START, &[BAD_HANDLE, BAD_HANDLE, 0, 0], ERR_BAD_HANDLE);
+
+    s.check("thread_create/options", nr::THREAD_CREATE, &[0, 0, 0, 1], ERR_INVALID_ARGS);
+    s.check("thread_create/name_too_long", nr::THREAD_CREATE, &[0, name, 65, 0], ERR_INVALID_ARGS);
+    s.check("thread_create/null_name", nr::THREAD_CREATE, &[0, 0, 4, 0], ERR_INVALID_ARGS);
+    s.check("thread_start/bad_handle", nr::THREAD_START, &[BAD_HANDLE, 0, 0, 0, 0], ERR_BAD_HANDLE);
+
+    s.check("job_create/bad_parent", nr::JOB_CREATE, &[BAD_HANDLE, 0], ERR_BAD_HANDLE);
+}
+
+fn handles(s: &mut Suite) {
+    let out = scratch(0);
+    s.check("handle_close/invalid", nr::HANDLE_CLOSE, &[0], OK);
+    s.check("handle_close/bad_handle", nr::HANDLE_CLOSE, &[BAD_HANDLE], ERR_BAD_HANDLE);
+
+    s.check("handle_duplicate/bad_handle", nr::HANDLE_DUPLICATE, &[BAD_HANDLE, 0x8000_0000, out], ERR_BAD_HANDLE);
+
+    s.check("handle_transfer/options", nr::HANDLE_TRANSFER, &[BAD_HANDLE, 0, 1], ERR_INVALID_ARGS);
+    s.check("handle_transfer/invalid", nr::HANDLE_TRANSFER, &[0, 0, 0], ERR_INVALID_ARGS);
+    s.check("handle_transfer/bad_handle", nr::HANDLE_TRANSFER, &[BAD_HANDLE, 0, 0], ERR_BAD_HANDLE);
+
+    s.check("handle_close_many/empty", nr::HANDLE_CLOSE_MANY, &[0, 0], OK);
+    s.check("handle_close_many/too_many", nr::HANDLE_CLOSE_MANY, &[scratch(0), 1025], ERR_INVALID_ARGS);
+
+    // One bad entry leaves the good ones open
+    let counter = unsafe { syscall::syscall1(nr::COUNTER_CREATE, 0) } as i64;
+    if counter >= 0 {
+        let handles = scratch(0);
+        unsafe {
+            core::ptr::write_unaligned(handles as *mut u32, counter as u32);
+            core::ptr::write_unaligned((handles + 4) as *mut u32, BAD_HANDLE as u32);
+        }
+        s.check("handle_close_many/bad_handle", nr::HANDLE_CLOSE_MANY, &[handles, 2], ERR_BAD_HANDLE);
+        unsafe { core::ptr::write_unaligned((handles + 4) as *mut u32, counter as u32) };
+        s.check("handle_close_many/repeated", nr::HANDLE_CLOSE_MANY, &[handles, 2], ERR_BAD_HANDLE);
+        s.check("handle_close_many/still_open", nr::HANDLE_CLOSE_MANY, &[handles, 1], OK);
+        unsafe { syscall::syscall1(nr::HANDLE_CLOSE, counter as u64) };
+    }
+
+    // Koids below 1024 are never handed out
+    s.check("object_get_child/bad_handle", nr::OBJECT_GET_CHILD, &[BAD_HANDLE, 1, 0], ERR_BAD_HANDLE);
+    s.check("object_get_child/not_found", nr::OBJECT_GET_CHILD, &[0, 1, 0], ERR_NOT_FOUND);
+}
+
+fn vmo(s: &mut Suite) {
+    let buf = scratch(0);
+    s.check("vmo_create/zero_size", nr::VMO_CREATE, &[0, 0], ERR_INVALID_ARGS);
+    s.check("vmo_create/unaligned_size", nr::VMO_CREATE, &[PAGE_SIZE + 1, 0], ERR_INVALID_ARGS);
+    s.check("vmo_create/options", nr::VMO_CREATE, &[PAGE_SIZE, 2], ERR_INVALID_ARGS);
+
+    s.check("vmo_read/zero_len", nr::VMO_READ, &[BAD_HANDLE, 0, 0, 0], OK);
+    s.check("vmo_read/null_buffer", nr::VMO_READ, &[BAD_HANDLE, 0, 0, 16], ERR_INVALID_ARGS);
+    s.check("vmo_read/kernel_buffer", nr::VMO_READ, &[BAD_HANDLE, KERNEL_PTR, 0, 16], ERR_INVALID_ARGS);
+    s.check("vmo_read/too_long", nr::VMO_READ, &[BAD_HANDLE, buf, 0, 0x100_0001], ERR_INVALID_ARGS);
+    s.check("vmo_read/bad_handle", nr::VMO_READ, &[BAD_HANDLE, buf, 0, 16], ERR_BAD_HANDLE);
+
+    s.check("vmo_write/zero_len", nr::VMO_WRITE, &[BAD_HANDLE, 0, 0, 0], OK);
+    s.check("vmo_write/kernel_buffer", nr::VMO_WRITE, &[BAD_HANDLE, KERNEL_PTR, 0, 16], ERR_INVALID_ARGS);
+    s.check("vmo_write/bad_handle", nr::VMO_WRITE, &[BAD_HANDLE, buf, 0, 16], ERR_BAD_HANDLE);
+
+    s.check("vmo_clone/zero_size", nr::VMO_CLONE, &[BAD_HANDLE, 0, 0, 0], ERR_INVALID_ARGS);
+    s.check("vmo_clone/bad_handle", nr::VMO_CLONE, &[BAD_HANDLE, 0, 0, PAGE_SIZE], ERR_BAD_HANDLE);
+
+    s.check("vmo_set_size/unaligned_size", nr::VMO_SET_SIZE, &[BAD_HANDLE, PAGE_SIZE + 1], ERR_INVALID_ARGS);
+    s.check("vmo_set_size/bad_handle", nr::VMO_SET_SIZE, &[BAD_HANDLE, PAGE_SIZE], ERR_BAD_HANDLE);
+
+    s.check("vmo_op_range/bad_op", nr::VMO_OP_RANGE, &[BAD_HANDLE, 3, 0, PAGE_SIZE], ERR_INVALID_ARGS);
+    s.check("vmo_op_range/bad_handle", nr::VMO_OP_RANGE, &[BAD_HANDLE, 1, 0, PAGE_SIZE], ERR_BAD_HANDLE);
+    s.check("vmo_op_range/try_lock_bad_handle", nr::VMO_OP_RANGE, &[BAD_HANDLE, 21, 0, PAGE_SIZE], ERR_BAD_HANDLE);
+    s.check("vmo_transfer_data/options", nr::VMO_TRANSFER_DATA, &[BAD_HANDLE, 1, 0, PAGE_SIZE, BAD_HANDLE, 0], ERR_INVALID_ARGS);
+    s.check("vmo_transfer_data/bad_handle", nr::VMO_TRANSFER_DATA, &[BAD_HANDLE, 0, 0, PAGE_SIZE, BAD_HANDLE, 0], ERR_BAD_HANDLE);
+
+    s.check("vmo_set_cache_policy/bad_policy", nr::VMO_SET_CACHE_POLICY, &[BAD_HANDLE, 4], ERR_INVALID_ARGS);
+    s.check("vmo_set_cache_policy/bad_handle", nr::VMO_SET_CACHE_POLICY, &[BAD_HANDLE, 1], ERR_BAD_HANDLE);
+}
+
+fn vmar(s: &mut Suite) {
+    // Handle 0 is the root VMAR
+    s.check("vmar_map/zero_len", nr::VMAR_MAP, &[0, 0, 0, BAD_HANDLE, 0, 0], ERR_INVALID_ARGS);
+    s.check("vmar_map/unaligned_len", nr::VMAR_MAP, &[0, 0, 0, BAD_HANDLE, 0, 1], ERR_INVALID_ARGS);
+    s.check("vmar_map/bad_vmar", nr::VMAR_MAP, &[BAD_HANDLE, 0, 0, BAD_HANDLE, 0, PAGE_SIZE], ERR_BAD_HANDLE);
+
+    s.check("vmar_unmap/zero_len", nr::VMAR_UNMAP, &[0, PAGE_SIZE, 0], ERR_INVALID_ARGS);
+    s.check("vmar_unmap/unaligned_addr", nr::VMAR_UNMAP, &[0, PAGE_SIZE + 1, PAGE_SIZE], ERR_INVALID_ARGS);
+    s.check("vmar_unmap/bad_vmar", nr::VMAR_UNMAP, &[BAD_HANDLE, PAGE_SIZE, PAGE_SIZE], ERR_BAD_HANDLE);
+
+    s.check("vmar_protect/unaligned_len", nr::VMAR_PROTECT, &[0, 0, PAGE_SIZE, 1], ERR_INVALID_ARGS);
+    s.check("vmar_protect/bad_vmar", nr::VMAR_PROTECT, &[BAD_HANDLE, 0, PAGE_SIZE, PAGE_SIZE], ERR_BAD_HANDLE);
+}
+
+fn channel(s: &mut Suite) {
+    let buf = scratch(0);
+    s.check("channel_create/options", nr::CHANNEL_CREATE, &[1], ERR_INVALID_ARGS);
+
+    s.check("channel_write/options", nr::CHANNEL_WRITE, &[BAD_HANDLE, 1, buf, 0, 0, 0], ERR_INVALID_ARGS);
+    s.check("channel_write/too_many_bytes", nr::CHANNEL_WRITE, &[BAD_HANDLE, 0, buf, MAX_MSG_SIZE + 1, 0, 0], ERR_OUT_OF_RANGE);
+    s.check("channel_write/too_many_handles", nr::CHANNEL_WRITE, &[BAD_HANDLE, 0, buf, 0, buf, MAX_MSG_HANDLES + 1], ERR_OUT_OF_RANGE);
+    s.check("channel_write/zero_len", nr::CHANNEL_WRITE, &[BAD_HANDLE, 0, 0, 0, 0, 0], ERR_BAD_HANDLE);
+
+    s.check("channel_read/options", nr::CHANNEL_READ, &[BAD_HANDLE, 2, buf, 16, 0, 0], ERR_INVALID_ARGS);
+    s.check("channel_read/bad_handle", nr::CHANNEL_READ, &[BAD_HANDLE, 0, buf, 16, 0, 0], ERR_BAD_HANDLE);
+
+    // Iovec writes count iovecs, not bytes, against the size check
+    s.check("channel_write/iovec_bad_handle", nr::CHANNEL_WRITE, &[BAD_HANDLE, 2, buf, MAX_MSG_SIZE + 1, 0, 0], ERR_BAD_HANDLE);
+
+    s.check("channel_write_etc/options", nr::CHANNEL_WRITE_ETC, &[BAD_HANDLE, 1, buf, 0, 0, 0], ERR_INVALID_ARGS);
+    s.check("channel_write_etc/too_many_handles", nr::CHANNEL_WRITE_ETC, &[BAD_HANDLE, 0, buf, 0, buf, MAX_MSG_HANDLES + 1], ERR_OUT_OF_RANGE);
+    s.check("channel_write_etc/bad_handle", nr::CHANNEL_WRITE_ETC, &[BAD_HANDLE, 0, buf, 16, 0, 0], ERR_BAD_HANDLE);
+
+    s.check("channel_read_etc/options", nr::CHANNEL_READ_ETC, &[BAD_HANDLE, 2, buf, 16, 0, 0], ERR_INVALID_ARGS);
+    s.check("channel_read_etc/bad_handle", nr::CHANNEL_READ_ETC, &[BAD_HANDLE, 0, buf, 16, 0, 0], ERR_BAD_HANDLE);
+}
+
+fn signals(s: &mut Suite) {
+    s.check("object_signal/bad_handle", nr::OBJECT_SIGNAL, &[BAD_HANDLE, 0, 0], ERR_BAD_HANDLE);
+    s.check("object_signal_peer/bad_handle", nr::OBJECT_SIGNAL_PEER, &[BAD_HANDLE, 0, USER_0], ERR_BAD_HANDLE);
+
+    // Both sides of a pair come back packed, the first in the low half
+    let pair = unsafe { syscall::syscall1(nr::EVENTPAIR_CREATE, 0) } as i64;
+    if pair >= 0 {
+        let (a, b) = (pair as u64 & 0xFFFF_FFFF, pair as u64 >> 32);
+        s.check("object_signal/eventpair_non_user_bits", nr::OBJECT_SIGNAL, &[a, 0, 1], ERR_INVALID_ARGS);
+        s.check("object_signal_peer/non_user_bits", nr::OBJECT_SIGNAL_PEER, &[a, 1, 0], ERR_INVALID_ARGS);
+
+        // Closing one side's only handle leaves the other side peer closed
+        unsafe { syscall::syscall1(nr::HANDLE_CLOSE, b) };
+        s.check("object_signal_peer/peer_closed", nr::OBJECT_SIGNAL_PEER, &[a, 0, USER_0], ERR_PEER_CLOSED);
+        unsafe { syscall::syscall1(nr::HANDLE_CLOSE, a) };
+    }
+
+    // A deadline of 0 is already in the past
+    s.check("object_wait_one/invalid", nr::OBJECT_WAIT_ONE, &[0, 1, 0, 0], ERR_BAD_HANDLE);
+    s.check("object_wait_many/no_items_past_deadline", nr::OBJECT_WAIT_MANY, &[0, 0, 0], ERR_TIMED_OUT);
+    s.check("object_wait_many/too_many_items", nr::OBJECT_WAIT_MANY, &[scratch(0), MAX_WAIT_ITEMS + 1, 0], ERR_OUT_OF_RANGE);
+    s.check("object_wait_many/null_items", nr::OBJECT_WAIT_MANY, &[0, 1, 0], ERR_INVALID_ARGS);
+    s.check("object_wait_many/kernel_items", nr::OBJECT_WAIT_MANY, &[KERNEL_PTR, 1, 0], ERR_INVALID_ARGS);
+
+    // Items are copied bytewise, so a misaligned array is read like any other
+    s.check("object_wait_many/invalid_item", nr::OBJECT_WAIT_MANY, &[scratch(0), 1, 0], ERR_BAD_HANDLE);
+    s.check("object_wait_many/misaligned_items", nr::OBJECT_WAIT_MANY, &[scratch(1), 1, 0], ERR_BAD_HANDLE);
+
+    // A valid item that is not signaled times out at a past deadline
+    let counter = unsafe { syscall::syscall1(nr::COUNTER_CREATE, 0) } as i64;
+    if counter >= 0 {
+        // struct { handle: u32, waitfor: u64, pending: u64 }, C layout
+        let items = scratch(0);
+        unsafe {
+            core::ptr::write_unaligned(items as *mut u32, counter as u32);
+            core::ptr::write_unaligned((items + 8) as *mut u64, COUNTER_POSITIVE);
+        }
+        s.check("object_wait_many/past_deadline", nr::OBJECT_WAIT_MANY, &[items, 1, 0], ERR_TIMED_OUT);
+        unsafe { syscall::syscall1(nr::HANDLE_CLOSE, counter as u64) };
+    }
+}
+
+fn counter(s: &mut Suite) {
+    s.check("counter_create/options", nr::COUNTER_CREATE, &[1], ERR_INVALID_ARGS);
+    s.check("counter_add/bad_handle", nr::COUNTER_ADD, &[BAD_HANDLE, 1], ERR_BAD_HANDLE);
+    s.check("counter_read/bad_handle", nr::COUNTER_READ, &[BAD_HANDLE, scratch(0)], ERR_BAD_HANDLE);
+    s.check("counter_write/bad_handle", nr::COUNTER_WRITE, &[BAD_HANDLE, 1], ERR_BAD_HANDLE);
+
+    let counter = unsafe { syscall::syscall1(nr::COUNTER_CREATE, 0) } as i64;
+    if counter >= 0 {
+        let counter = counter as u64;
+        s.check("counter_read/null", nr::COUNTER_READ, &[counter, 0], ERR_INVALID_ARGS);
+
+        // An overflowing add leaves the value alone
+        unsafe { syscall::syscall2(nr::COUNTER_WRITE, counter, i64::MAX as u64) };
+        s.check("counter_add/overflow", nr::COUNTER_ADD, &[counter, 1], ERR_OUT_OF_RANGE);
+        unsafe { syscall::syscall1(nr::HANDLE_CLOSE, counter) };
+    }
+}
+
+fn time(s: &mut Suite) {
+    s.check("clock_get/bad_clock", nr::CLOCK_GET, &[7], ERR_INVALID_ARGS);
+
+    s.check("timer_create/options", nr::TIMER_CREATE, &[1, 0], ERR_INVALID_ARGS);
+    s.check("timer_create/bad_clock", nr::TIMER_CREATE, &[0, 7], ERR_INVALID_ARGS);
+    s.check("timer_set/negative_slack", nr::TIMER_SET, &[BAD_HANDLE, 0, -1i64 as u64], ERR_OUT_OF_RANGE);
+    s.check("timer_set/bad_handle_past_deadline", nr::TIMER_SET, &[BAD_HANDLE, 0, 0], ERR_BAD_HANDLE);
+    s.check("timer_cancel/bad_handle", nr::TIMER_CANCEL, &[BAD_HANDLE], ERR_BAD_HANDLE);
+}
+
+fn cprng(s: &mut Suite) {
+    s.check("cprng_draw/zero_len", nr::CPRNG_DRAW, &[0, 0], OK);
+    s.check("cprng_draw/too_long", nr::CPRNG_DRAW, &[scratch(0), MAX_CPRNG_LEN + 1], ERR_INVALID_ARGS);
+    s.check("cprng_draw/null_buffer", nr::CPRNG_DRAW, &[0, 16], ERR_INVALID_ARGS);
+    s.check("cprng_draw/kernel_buffer", nr::CPRNG_DRAW, &[KERNEL_PTR, 16], ERR_INVALID_ARGS);
+    s.check("cprng_draw/misaligned_buffer", nr::CPRNG_DRAW, &[scratch(3), 16], OK);
+
+    s.check("cprng_add_entropy/zero_len", nr::CPRNG_ADD_ENTROPY, &[0, 0], OK);
+    s.check("cprng_add_entropy/too_long", nr::CPRNG_ADD_ENTROPY, &[scratch(0), MAX_CPRNG_LEN + 1], ERR_INVALID_ARGS);
+    s.check("cprng_add_entropy/kernel_buffer", nr::CPRNG_ADD_ENTROPY, &[KERNEL_PTR, 16], ERR_INVALID_ARGS);
+}
+
+fn system_info(s: &mut Suite) {
+    s.check("system_get_version/ok", nr::SYSTEM_GET_VERSION, &[scratch(0)], OK);
+    s.check("system_get_version/null_buffer", nr::SYSTEM_GET_VERSION, &[0], ERR_INVALID_ARGS);
+    s.check("system_get_version/kernel_buffer", nr::SYSTEM_GET_VERSION, &[KERNEL_PTR], ERR_INVALID_ARGS);
+
+    // The syscall bitmap is 32 bytes
+    s.check("system_get_features/ok", nr::SYSTEM_GET_FEATURES, &[0, scratch(0), 32], OK);
+    s.check("system_get_features/bad_kind", nr::SYSTEM_GET_FEATURES, &[1, scratch(0), 32], ERR_INVALID_ARGS);
+    s.check("system_get_features/too_small", nr::SYSTEM_GET_FEATURES, &[0, scratch(0), 31], ERR_BUFFER_TOO_SMALL);
+}
+
+fn resources(s: &mut Suite) {
+    // Only the root resource may read kernel diagnostics
+    let buf = scratch(0);
+    s.check("powerctl/not_root", nr::SYSTEM_POWERCTL, &[BAD_HANDLE, 8, 0], ERR_ACCESS_DENIED);
+    s.check("crashlog_read/not_root", nr::CRASHLOG_READ, &[BAD_HANDLE, 0, buf, 16, 0], ERR_ACCESS_DENIED);
+    s.check("kcounter_read/not_root", nr::KCOUNTER_READ, &[BAD_HANDLE, buf, 16, 0, 0], ERR_ACCESS_DENIED);
+    s.check("ktrace_read/not_root", nr::KTRACE_READ, &[BAD_HANDLE, buf, 0, 16, 0], ERR_ACCESS_DENIED);
+    s.check("system_get_event/not_root", nr::SYSTEM_GET_EVENT, &[BAD_HANDLE, 1], ERR_ACCESS_DENIED);
+
+    // Resources derive from the root or a covering resource of their kind,
+    // and nothing derives a root
+    s.check("resource_create/not_root", nr::RESOURCE_CREATE, &[BAD_HANDLE, 2, 0x1000, 0x1000, 0, 0], ERR_ACCESS_DENIED);
+    s.check("resource_create/root_kind", nr::RESOURCE_CREATE, &[BAD_HANDLE, 0, 0, 0, 0, 0], ERR_INVALID_ARGS);
+    s.check("resource_create/empty_range", nr::RESOURCE_CREATE, &[BAD_HANDLE, 1, 32, 0, 0, 0], ERR_INVALID_ARGS);
+}
+
+fn hypervisor(s: &mut Suite) {
+    // Guests need the root or a hypervisor resource
+    s.check("hypervisor_create/not_root", nr::HYPERVISOR_CREATE, &[BAD_HANDLE, 0], ERR_ACCESS_DENIED);
+    s.check("hypervisor_create/options", nr::HYPERVISOR_CREATE, &[BAD_HANDLE, 1], ERR_INVALID_ARGS);
+
+    s.check("hypervisor_op/bad_handle", nr::HYPERVISOR_OP, &[BAD_HANDLE, 2, 0, 0, 0, 0x1000], ERR_BAD_HANDLE);
+    s.check("guest_set_trap/bad_handle", nr::GUEST_SET_TRAP, &[BAD_HANDLE, 1, 0, 0x1000, 0, 0], ERR_BAD_HANDLE);
+    s.check("vcpu_create/bad_handle", nr::VCPU_CREATE, &[BAD_HANDLE, 0, 0], ERR_BAD_HANDLE);
+    s.check("vcpu_create/options", nr::VCPU_CREATE, &[BAD_HANDLE, 1, 0], ERR_INVALID_ARGS);
+
+    let buf = scratch(0);
+    s.check("vcpu_enter/bad_handle", nr::VCPU_ENTER, &[BAD_HANDLE, buf], ERR_BAD_HANDLE);
+    s.check("vcpu_kick/bad_handle", nr::VCPU_KICK, &[BAD_HANDLE], ERR_BAD_HANDLE);
+    s.check("vcpu_interrupt/bad_handle", nr::VCPU_INTERRUPT, &[BAD_HANDLE, 32], ERR_BAD_HANDLE);
+    s.check("vcpu_read_state/bad_handle", nr::VCPU_READ_STATE, &[BAD_HANDLE, 0, buf, 8], ERR_BAD_HANDLE);
+    s.check("vcpu_write_state/bad_handle", nr::VCPU_WRITE_STATE, &[BAD_HANDLE, 0, buf, 8], ERR_BAD_HANDLE);
+}
+
+fn fifo(s: &mut Suite) {
+    s.check("fifo_create/options", nr::FIFO_CREATE, &[4, 8, 1, 0, 0], ERR_INVALID_ARGS);
+    s.check("fifo_create/zero_count", nr::FIFO_CREATE, &[0, 8, 0, 0, 0], ERR_INVALID_ARGS);
+    s.check("fifo_create/zero_elem_size", nr::FIFO_CREATE, &[4, 0, 0, 0, 0], ERR_INVALID_ARGS);
+    s.check("fifo_create/kernel_out", nr::FIFO_CREATE, &[4, 8, 0, KERNEL_PTR, 0], ERR_INVALID_ARGS);
+
+    s.check("fifo_write/bad_handle", nr::FIFO_WRITE, &[BAD_HANDLE, 8, 0, 1, 0], ERR_BAD_HANDLE);
+    s.check("fifo_read/bad_handle", nr::FIFO_READ, &[BAD_HANDLE, 8, 0, 1, 0], ERR_BAD_HANDLE);
+
+    // A real pair for the argument checks past the handle lookup; the
+    // handles land at odd addresses to cover misaligned out pointers
+    let out = scratch(1);
+    if s.check("fifo_create/misaligned_out", nr::FIFO_CREATE, &[4, 8, 0, out, out + 8], OK) != OK {
+        return;
+    }
+    let (a, b) = (read_u64(out), read_u64(out + 8));
+    let buf = scratch(64);
+
+    s.check("fifo_write/elem_size", nr::FIFO_WRITE, &[a, 4, buf, 1, 0], ERR_INVALID_ARGS);
+    s.check("fifo_write/zero_count", nr::FIFO_WRITE, &[a, 8, buf, 0, 0], ERR_OUT_OF_RANGE);
+    s.check("fifo_write/kernel_buffer", nr::FIFO_WRITE, &[a, 8, KERNEL_PTR, 1, 0], ERR_INVALID_ARGS);
+    s.check("fifo_read/elem_size", nr::FIFO_READ, &[b, 4, buf, 1, 0], ERR_INVALID_ARGS);
+    s.check("fifo_read/zero_count", nr::FIFO_READ, &[b, 8, buf, 0, 0], ERR_OUT_OF_RANGE);
+    s.check("fifo_read/empty", nr::FIFO_READ, &[b, 8, buf, 1, 0], ERR_SHOULD_WAIT);
+}
+
+fn dispatch(s: &mut Suite) {
+    s.check("syscall/unassigned", nr::UNASSIGNED, &[], ERR_NOT_SUPPORTED);
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let filter = if argc > 1 { unsafe { arg(argv, 1) } } else { None };
+    let mut suite = Suite { out: StdoutWriter, filter, passed: 0, failed: 0 };
+
+    task(&mut suite);
+    handles(&mut suite);
+    vmo(&mut suite);
+    vmar(&mut suite);
+    channel(&mut suite);
+    signals(&mut suite);
+    counter(&mut suite);
+    time(&mut suite);
+    cprng(&mut suite);
+    system_info(&mut suite);
+    resources(&mut suite);
+    hypervisor(&mut suite);
+    fifo(&mut suite);
+    dispatch(&mut suite);
+
+    let _ = writeln!(suite.out, "core-tests: {} passed, {} failed", suite.passed, suite.failed);
+    suite.failed as i32
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
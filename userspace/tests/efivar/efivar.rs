@@ -0,0 +1,288 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! efivar - Read and Write EFI Variables
+//!
+//! Uses the firmware's runtime services, so it only works on a system
+//! booted through the UEFI loader. `<guid>` is a vendor GUID in the usual
+//! `8be4df61-93ca-11d2-aa0d-00e098032b8c` form, or `global` for the UEFI
+//! global variables (`BootOrder`, `Boot0000`, `SecureBoot`, ...).
+//!
+//! `get` prints the attributes and a hexdump of the value, or with `-s`
+//! the value as text. `set` stores text, or with `-x` hex bytes, as a
+//! non-volatile variable visible at runtime; `-v` makes it volatile.
+//!
+//! Usage:
+//!
+//! - `efivar get [-s] <guid> <name>`
+//! - `efivar set [-x] [-v] <guid> <name> <value>`
+//! - `efivar delete <guid> <name>`
+//! - `efivar time`
+
+#![no_std]
+#![no_main]
+
+extern crate libsys;
+extern crate rt;
+
+use core::fmt::Write;
+
+use libsys::efi::{self, Guid};
+use libsys::*;
+
+const USAGE: &str =
+    "usage: efivar get [-s] <guid> <name> | set [-x] [-v] <guid> <name> <value> | delete <guid> <name> | time";
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Value of a hex digit
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parse hex bytes into `out`, returning the number parsed
+fn parse_hex(s: &str, out: &mut [u8]) -> Option<usize> {
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 || s.len() / 2 > out.len() {
+        return None;
+    }
+    for (i, pair) in s.chunks_exact(2).enumerate() {
+        out[i] = hex_digit(pair[0])? << 4 | hex_digit(pair[1])?;
+    }
+    Some(s.len() / 2)
+}
+
+/// Parse a GUID in its text form into EFI byte order
+///
+/// The first three fields are stored little-endian, the last two as
+/// written.
+fn parse_guid(s: &str) -> Option<Guid> {
+    if s == "global" {
+        return Some(efi::GLOBAL_VARIABLE);
+    }
+
+    let mut fields = s.split('-');
+    let mut text = [0u8; 16];
+    for (len, at) in [(4, 0), (2, 4), (2, 6), (2, 8), (6, 10)] {
+        let field = fields.next()?;
+        if field.len() != len * 2 || parse_hex(field, &mut text[at..at + len])? != len {
+            return None;
+        }
+    }
+    if fields.next().is_some() {
+        return None;
+    }
+
+    let mut guid = text;
+    guid[0..4].reverse();
+    guid[4..6].reverse();
+    guid[6..8].reverse();
+    Some(guid)
+}
+
+/// Convert an ASCII name to UCS-2, returning its length
+fn parse_name(s: &str, out: &mut [u16; efi::VARIABLE_NAME_MAX]) -> Option<usize> {
+    if s.is_empty() || s.len() > out.len() || !s.is_ascii() {
+        return None;
+    }
+    for (c, b) in out.iter_mut().zip(s.bytes()) {
+        *c = b as u16;
+    }
+    Some(s.len())
+}
+
+fn print_attributes(w: &mut StdoutWriter, attributes: u32) {
+    let _ = write!(w, "attributes {:#x}", attributes);
+    for (bit, name) in [
+        (efi::VARIABLE_NON_VOLATILE, "NV"),
+        (efi::VARIABLE_BOOTSERVICE_ACCESS, "BS"),
+        (efi::VARIABLE_RUNTIME_ACCESS, "RT"),
+    ] {
+        if attributes & bit != 0 {
+            let _ = write!(w, " {}", name);
+        }
+    }
+    let _ = writeln!(w);
+}
+
+fn hexdump(w: &mut StdoutWriter, data: &[u8]) {
+    for (row, chunk) in data.chunks(16).enumerate() {
+        let _ = write!(w, "{:04x}:", row * 16);
+        for byte in chunk {
+            let _ = write!(w, " {:02x}", byte);
+        }
+        for _ in chunk.len()..16 {
+            let _ = write!(w, "   ");
+        }
+        let _ = write!(w, "  ");
+        for &byte in chunk {
+            let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+            let _ = write!(w, "{}", c);
+        }
+        let _ = writeln!(w);
+    }
+}
+
+fn get(root: &Handle, w: &mut StdoutWriter, guid: &Guid, name: &[u16], text: bool) -> Result<()> {
+    let mut data = [0u8; efi::VARIABLE_DATA_MAX];
+    let (attributes, size) = efi::get_variable(root, guid, name, &mut data)?;
+    let data = &data[..size];
+
+    print_attributes(w, attributes);
+    if text {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        let _ = writeln!(w, "{}", core::str::from_utf8(&data[..end]).unwrap_or("<not UTF-8>"));
+    } else {
+        hexdump(w, data);
+    }
+    Ok(())
+}
+
+fn time(root: &Handle, w: &mut StdoutWriter) -> Result<()> {
+    let t = efi::get_time(root)?;
+    let _ = write!(
+        w,
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second, t.nanosecond
+    );
+    match t.time_zone {
+        2047 => {
+            let _ = writeln!(w, " (local time)");
+        }
+        tz => {
+            let _ = writeln!(w, " (time zone {} minutes)", tz);
+        }
+    }
+    Ok(())
+}
+
+fn run(root: &Handle, argc: isize, argv: *const *const u8, w: &mut StdoutWriter) -> Option<Result<()>> {
+    let command = unsafe { arg(argv, 1) }?;
+    if command == "time" {
+        return if argc == 2 { Some(time(root, w)) } else { None };
+    }
+
+    // Flags, then the GUID, the name and for `set` the value
+    let mut text = false;
+    let mut hex = false;
+    let mut volatile = false;
+    let mut i = 2;
+    while i < argc {
+        match unsafe { arg(argv, i) }? {
+            "-s" if command == "get" => text = true,
+            "-x" if command == "set" => hex = true,
+            "-v" if command == "set" => volatile = true,
+            _ => break,
+        }
+        i += 1;
+    }
+
+    let operands = if command == "set" { 3 } else { 2 };
+    if argc - i != operands {
+        return None;
+    }
+    let guid = parse_guid(unsafe { arg(argv, i) }?)?;
+    let mut name = [0u16; efi::VARIABLE_NAME_MAX];
+    let name_len = parse_name(unsafe { arg(argv, i + 1) }?, &mut name)?;
+    let name = &name[..name_len];
+
+    Some(match command {
+        "get" => get(root, w, &guid, name, text),
+        "set" => {
+            let value = unsafe { arg(argv, i + 2) }?;
+            let mut data = [0u8; efi::VARIABLE_DATA_MAX];
+            let len = if hex {
+                parse_hex(value, &mut data)?
+            } else {
+                let len = value.len().min(data.len());
+                data[..len].copy_from_slice(&value.as_bytes()[..len]);
+                len
+            };
+
+            let mut attributes = efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+            if !volatile {
+                attributes |= efi::VARIABLE_NON_VOLATILE;
+            }
+            efi::set_variable(root, &guid, name, attributes, &data[..len])
+        }
+        "delete" => efi::delete_variable(root, &guid, name),
+        _ => return None,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "efivar: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&root, argc.max(0) as isize, argv, &mut writer) {
+        Some(Ok(())) => 0,
+        Some(Err(e)) if e.status() == Status::NotSupported => {
+            let _ = writeln!(writer, "efivar: no EFI runtime services");
+            1
+        }
+        Some(Err(e)) if e.status() == Status::NotFound => {
+            let _ = writeln!(writer, "efivar: no such variable");
+            1
+        }
+        Some(Err(e)) if e.status() == Status::AccessDenied => {
+            let _ = writeln!(writer, "efivar: the firmware refused the write");
+            1
+        }
+        Some(Err(e)) => {
+            let _ = writeln!(writer, "efivar: {:?}", e);
+            1
+        }
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
@@ -0,0 +1,284 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ipc-bench - Compare IPC Transports
+//!
+//! Times round trips between two threads, for several message sizes, over
+//! a channel and over a pair of shared-memory rings ([`ipc::ring`]). A
+//! second thread echoes every message back. Channels make two syscalls
+//! per hop and copy through the kernel; rings copy once into shared
+//! memory and only make a syscall to wake a sleeping peer.
+//!
+//! Channel round trips are timed both as a write and a read and as one
+//! `rx_channel_call_etc`, which hands the caller's time slice and priority
+//! to the echo thread. The last table repeats the small-message channel
+//! runs with busy threads competing for the CPU, where a plain write
+//! leaves the echo thread queued behind them and a call does not.
+//!
+//! Usage: `ipc-bench [iterations]`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate ipc;
+extern crate libsys;
+extern crate rt;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ipc::{Channel, Consumer, Producer, Ring};
+use libsys::*;
+use rt::timer::get_monotonic_time;
+use rt::Thread;
+
+/// Round trips per message size by default
+const DEFAULT_ITERATIONS: u64 = 10_000;
+
+/// Message sizes measured
+const SIZES: [usize; 5] = [16, 256, 4096, 16384, 60000];
+
+/// Data area of each ring; holds a few of the largest messages
+const RING_CAPACITY: usize = 256 * 1024;
+
+/// Largest message, and the echo threads' buffer size
+const MAX_MESSAGE: usize = 64 * 1024;
+
+/// Busy threads competing with the echo thread in the contended runs
+const CONTENDERS: usize = 4;
+
+/// Message size of the contended runs
+const CONTENDED_SIZE: usize = 16;
+
+/// Tells the busy threads to stop
+static STOP: AtomicBool = AtomicBool::new(false);
+
+const USAGE: &str = "usage: ipc-bench [iterations]";
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Read the next message from `channel`, yielding until one arrives
+fn channel_recv(channel: &Channel, buf: &mut [u8], handles: &mut Vec<Handle>) -> Result<usize> {
+    loop {
+        match channel.read(buf, handles) {
+            Ok(n) => return Ok(n),
+            Err(e) if e.status() == Status::BufferTooSmall => return Err(e),
+            // Nothing queued yet
+            Err(_) => Thread::yield_now(),
+        }
+    }
+}
+
+/// Echo every channel message back until an empty one arrives
+extern "C" fn channel_echo(arg: *mut u8) {
+    let channel = unsafe { Box::from_raw(arg as *mut Channel) };
+    let mut buf = alloc::vec![0u8; MAX_MESSAGE];
+    let mut handles = Vec::new();
+
+    while let Ok(n) = channel_recv(&channel, &mut buf, &mut handles) {
+        if n == 0 || channel.write(&buf[..n], &[]).is_err() {
+            break;
+        }
+    }
+}
+
+/// Echo every ring message back until an empty one arrives
+extern "C" fn ring_echo(arg: *mut u8) {
+    let (mut requests, mut replies) = *unsafe { Box::from_raw(arg as *mut (Consumer, Producer)) };
+    let mut buf = alloc::vec![0u8; MAX_MESSAGE];
+
+    while let Ok(n) = requests.recv(&mut buf) {
+        if n == 0 || replies.send(&buf[..n]).is_err() {
+            break;
+        }
+    }
+}
+
+/// Burn CPU until [`STOP`] is set
+extern "C" fn spin(_arg: *mut u8) {
+    while !STOP.load(Ordering::Relaxed) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Run `f` with [`CONTENDERS`] busy threads
+fn contended<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    STOP.store(false, Ordering::Relaxed);
+    let mut spinners = Vec::new();
+    for _ in 0..CONTENDERS {
+        spinners.push(Thread::spawn(spin, core::ptr::null_mut())?);
+    }
+
+    let result = f();
+
+    STOP.store(true, Ordering::Relaxed);
+    for spinner in spinners {
+        spinner.join()?;
+    }
+    result
+}
+
+/// Map a second view of `ring`, as a peer process would
+fn attach_peer(ring: &Ring) -> Result<Ring> {
+    let handle = ring.vmo().handle().duplicate(Rights::SAME_RIGHTS)?;
+    Ring::attach(unsafe { Vmo::from_handle(handle) })
+}
+
+/// Nanoseconds per round trip over a channel for each of `sizes`, as a
+/// call or as a write and a read
+fn bench_channel<const N: usize>(iterations: u64, sizes: [usize; N], call: bool) -> Result<[u64; N]> {
+    let (ours, theirs) = Channel::create()?;
+    let echo = Thread::spawn(channel_echo, Box::into_raw(Box::new(theirs)) as *mut u8)?;
+
+    let msg = alloc::vec![0x5Au8; MAX_MESSAGE];
+    let mut buf = alloc::vec![0u8; MAX_MESSAGE];
+    let mut handles = Vec::new();
+    let mut results = [0; N];
+
+    for (result, &size) in results.iter_mut().zip(sizes.iter()) {
+        let start = get_monotonic_time();
+        for _ in 0..iterations {
+            if call {
+                // The echo thread sends back the transaction ID with the rest
+                ours.call(&msg[..size], &[], &mut buf, &mut handles, 0)?;
+            } else {
+                ours.write(&msg[..size], &[])?;
+                channel_recv(&ours, &mut buf, &mut handles)?;
+            }
+        }
+        *result = (get_monotonic_time() - start) / iterations;
+    }
+
+    ours.write(&[], &[])?;
+    echo.join()?;
+    Ok(results)
+}
+
+/// Nanoseconds per round trip over a pair of rings, for each of [`SIZES`]
+fn bench_ring(iterations: u64) -> Result<[u64; SIZES.len()]> {
+    let requests = Ring::create(RING_CAPACITY)?;
+    let replies = Ring::create(RING_CAPACITY)?;
+    let peer = (attach_peer(&requests)?.into_consumer(), attach_peer(&replies)?.into_producer());
+    let echo = Thread::spawn(ring_echo, Box::into_raw(Box::new(peer)) as *mut u8)?;
+
+    let mut requests = requests.into_producer();
+    let mut replies = replies.into_consumer();
+    let msg = alloc::vec![0x5Au8; MAX_MESSAGE];
+    let mut buf = alloc::vec![0u8; MAX_MESSAGE];
+    let mut results = [0; SIZES.len()];
+
+    for (result, &size) in results.iter_mut().zip(SIZES.iter()) {
+        let start = get_monotonic_time();
+        for _ in 0..iterations {
+            requests.send(&msg[..size])?;
+            replies.recv(&mut buf)?;
+        }
+        *result = (get_monotonic_time() - start) / iterations;
+    }
+
+    requests.send(&[])?;
+    echo.join()?;
+    Ok(results)
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let iterations = match (argc > 1).then(|| unsafe { arg(argv, 1) }).flatten() {
+        None => DEFAULT_ITERATIONS,
+        Some(s) => match s.parse::<u64>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                let _ = writeln!(writer, "{}", USAGE);
+                return 1;
+            }
+        },
+    };
+
+    let channel = match bench_channel(iterations, SIZES, false) {
+        Ok(results) => results,
+        Err(e) => {
+            let _ = writeln!(writer, "ipc-bench: channel failed: {:?}", e);
+            return 1;
+        }
+    };
+    let call = match bench_channel(iterations, SIZES, true) {
+        Ok(results) => results,
+        Err(e) => {
+            let _ = writeln!(writer, "ipc-bench: call failed: {:?}", e);
+            return 1;
+        }
+    };
+    let ring = match bench_ring(iterations) {
+        Ok(results) => results,
+        Err(e) => {
+            let _ = writeln!(writer, "ipc-bench: ring failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    let contended = contended(|| {
+        let [channel] = bench_channel(iterations, [CONTENDED_SIZE], false)?;
+        let [call] = bench_channel(iterations, [CONTENDED_SIZE], true)?;
+        Ok((channel, call))
+    });
+    let (contended_channel, contended_call) = match contended {
+        Ok(results) => results,
+        Err(e) => {
+            let _ = writeln!(writer, "ipc-bench: contended run failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    let _ = writeln!(writer, "round trips: {} per size", iterations);
+    let _ = writeln!(writer, "{:>8} {:>14} {:>14} {:>14} {:>8}", "bytes", "channel ns", "call ns", "ring ns", "speedup");
+    for (((size, channel), call), ring) in SIZES.iter().zip(channel.iter()).zip(call.iter()).zip(ring.iter()) {
+        let speedup = *channel as f64 / (*ring).max(1) as f64;
+        let _ = writeln!(writer, "{:>8} {:>14} {:>14} {:>14} {:>7.1}x", size, channel, call, ring, speedup);
+    }
+
+    let _ = writeln!(writer, "with {} busy threads, {}-byte messages:", CONTENDERS, CONTENDED_SIZE);
+    let _ = writeln!(writer, "{:>14} {:>14} {:>8}", "channel ns", "call ns", "speedup");
+    let speedup = contended_channel as f64 / contended_call.max(1) as f64;
+    let _ = writeln!(writer, "{:>14} {:>14} {:>7.1}x", contended_channel, contended_call, speedup);
+    0
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
@@ -0,0 +1,419 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! itrace - Capture Hardware Instruction Trace
+//!
+//! `start` allocates trace buffers, one per CPU or, with `-t`, one for a
+//! thread, and starts tracing. `stop` stops it, `dump` decodes the
+//! buffers and `free` releases them. `-u` and `-k` trace only user or
+//! only kernel code, `-s` sets the buffer size in KiB.
+//!
+//! On x86 `dump` decodes Intel PT packets: taken and not-taken branches
+//! (`tnt`, `!` for taken), branch targets (`tip`), where tracing was
+//! enabled and disabled (`tip.pge`, `tip.pgd`), timestamps and
+//! overflows. It starts at the first PSB after the write offset, so a
+//! buffer that wrapped is read oldest first. On arm64 it prints the ETE
+//! trace as hex, for OpenCSD to decode; each CPU's trace ID is its
+//! number plus one.
+//!
+//! Usage:
+//!
+//! - `itrace start [-t <tid>] [-u|-k] [-s <KiB>]`
+//! - `itrace stop`
+//! - `itrace dump [<index>]`
+//! - `itrace free`
+
+#![no_std]
+#![no_main]
+
+extern crate libsys;
+extern crate rt;
+
+use core::fmt::Write;
+
+use libsys::itrace::{self, BufferInfo, Config};
+use libsys::*;
+
+const USAGE: &str = "usage: itrace start [-t <tid>] [-u|-k] [-s <KiB>] | stop | dump [<index>] | free";
+
+/// Default buffer size in KiB
+const DEFAULT_SIZE_KIB: u64 = 64;
+
+/// Bytes read from a buffer at a time
+const CHUNK: usize = 4096;
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Parse the options of `start`
+fn parse(argc: isize, argv: *const *const u8) -> Option<Config> {
+    let mut config = Config { buffer_size: DEFAULT_SIZE_KIB * 1024, ..Config::default() };
+    let mut i = 2;
+    while i < argc {
+        let a = unsafe { arg(argv, i) }?;
+        let value = if i + 1 < argc { unsafe { arg(argv, i + 1) } } else { None };
+        match a {
+            "-t" => {
+                config.mode = itrace::MODE_THREAD;
+                config.thread = value?.parse().ok()?;
+                i += 1;
+            }
+            "-s" => {
+                let kib: u64 = value?.parse().ok()?;
+                config.buffer_size = kib.checked_mul(1024)?;
+                i += 1;
+            }
+            "-u" => config.flags = itrace::FLAG_USER,
+            "-k" => config.flags = itrace::FLAG_KERNEL,
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(config)
+}
+
+fn start(root: &Handle, config: &Config) -> Result<()> {
+    itrace::alloc(root, config)?;
+    if let Err(e) = itrace::start(root) {
+        let _ = itrace::free(root);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// A buffer read through a window, from `offset` to the end and then
+/// from the start, so a wrapped buffer reads oldest first
+struct Reader {
+    vmo: Vmo,
+    size: u64,
+    offset: u64,
+    window: [u8; CHUNK],
+    window_pos: u64,
+    window_len: usize,
+}
+
+impl Reader {
+    fn new(vmo: Vmo, size: u64, offset: u64) -> Self {
+        Reader { vmo, size, offset: offset % size.max(1), window: [0; CHUNK], window_pos: 0, window_len: 0 }
+    }
+
+    /// Byte `pos` of the trace, counting from the oldest
+    fn byte(&mut self, pos: u64) -> Option<u8> {
+        if pos >= self.size {
+            return None;
+        }
+        let at = (self.offset + pos) % self.size;
+        if at < self.window_pos || at >= self.window_pos + self.window_len as u64 {
+            let len = (CHUNK as u64).min(self.size - at) as usize;
+            self.vmo.read(at, &mut self.window[..len]).ok()?;
+            self.window_pos = at;
+            self.window_len = len;
+        }
+        Some(self.window[(at - self.window_pos) as usize])
+    }
+
+    /// `len` bytes from `pos` as a little-endian integer
+    fn le(&mut self, pos: u64, len: usize) -> Option<u64> {
+        let mut value = 0;
+        for i in (0..len).rev() {
+            value = value << 8 | self.byte(pos + i as u64)? as u64;
+        }
+        Some(value)
+    }
+}
+
+/// PSB: `02 82` eight times
+fn is_psb(r: &mut Reader, pos: u64) -> bool {
+    (0..16).all(|i| r.byte(pos + i) == Some(if i % 2 == 0 { 0x02 } else { 0x82 }))
+}
+
+/// Write taken and not-taken branches, oldest first, from `bits` below
+/// bit `count`
+fn write_tnt(w: &mut StdoutWriter, bits: u64, count: u32) {
+    let _ = write!(w, "tnt      ");
+    for i in (0..count).rev() {
+        let _ = write!(w, "{}", if bits & (1 << i) != 0 { '!' } else { '.' });
+    }
+    let _ = writeln!(w);
+}
+
+/// Decode the Intel PT packets of a buffer
+///
+/// Returns how many packets were decoded.
+fn decode_pt(r: &mut Reader, w: &mut StdoutWriter) -> u64 {
+    let mut pos = 0;
+    let mut packets = 0;
+    let mut synced = false;
+    let mut last_ip: u64 = 0;
+
+    while r.byte(pos).is_some() {
+        if !synced {
+            if is_psb(r, pos) {
+                synced = true;
+            } else {
+                pos += 1;
+                continue;
+            }
+        }
+
+        let b = r.byte(pos).unwrap_or(0);
+        let len = match b {
+            // PAD
+            0x00 => 1,
+
+            // Extended opcodes
+            0x02 => match r.byte(pos + 1) {
+                Some(0x82) if is_psb(r, pos) => {
+                    // IP compression restarts at every PSB
+                    last_ip = 0;
+                    let _ = writeln!(w, "psb");
+                    16
+                }
+                Some(0x23) => 2,
+                Some(0x03) => {
+                    let _ = writeln!(w, "cbr      {}", r.byte(pos + 2).unwrap_or(0));
+                    4
+                }
+                Some(0xf3) => {
+                    let _ = writeln!(w, "ovf");
+                    2
+                }
+                Some(0xa3) => {
+                    let bits = r.le(pos + 2, 6).unwrap_or(0);
+                    if bits != 0 {
+                        write_tnt(w, bits, 63 - bits.leading_zeros());
+                    }
+                    8
+                }
+                Some(0x43) => 8,
+                Some(0x83) => {
+                    let _ = writeln!(w, "stop");
+                    2
+                }
+                Some(0x73) => 7,
+                Some(0xc8) => 7,
+                Some(0xc3) => 11,
+                _ => 0,
+            },
+
+            // MODE
+            0x99 => {
+                let payload = r.byte(pos + 1).unwrap_or(0);
+                if payload >> 5 == 0 {
+                    let mode = match payload & 0x3 {
+                        0x1 => "64-bit",
+                        0x2 => "32-bit",
+                        _ => "16-bit",
+                    };
+                    let _ = writeln!(w, "mode     {}", mode);
+                }
+                2
+            }
+
+            // TSC
+            0x19 => {
+                let _ = writeln!(w, "tsc      {:#x}", r.le(pos + 1, 7).unwrap_or(0));
+                8
+            }
+
+            // MTC
+            0x59 => 2,
+
+            // CYC: more bytes follow while the Exp bit is set
+            b if b & 0x3 == 0x3 => {
+                let mut len = 1;
+                let mut exp = b & 0x4 != 0;
+                while exp {
+                    exp = r.byte(pos + len).map_or(false, |b| b & 0x1 != 0);
+                    len += 1;
+                }
+                len
+            }
+
+            // Short TNT: the highest set bit stops the branches below it
+            b if b & 0x1 == 0 => {
+                let bits = (b >> 1) as u64;
+                write_tnt(w, bits, 63 - bits.leading_zeros());
+                1
+            }
+
+            // TIP, TIP.PGE, TIP.PGD and FUP, with compressed IPs
+            b if matches!(b & 0x1f, 0x0d | 0x11 | 0x01 | 0x1d) => {
+                let name = match b & 0x1f {
+                    0x0d => "tip     ",
+                    0x11 => "tip.pge ",
+                    0x01 => "tip.pgd ",
+                    _ => "fup     ",
+                };
+                let (bytes, ip) = match b >> 5 {
+                    0 => (0, None),
+                    1 => (2, Some(last_ip & !0xffff | r.le(pos + 1, 2).unwrap_or(0))),
+                    2 => (4, Some(last_ip & !0xffff_ffff | r.le(pos + 1, 4).unwrap_or(0))),
+                    3 => (6, Some((((r.le(pos + 1, 6).unwrap_or(0) << 16) as i64) >> 16) as u64)),
+                    4 => (6, Some(last_ip & !0xffff_ffff_ffff | r.le(pos + 1, 6).unwrap_or(0))),
+                    6 => (8, Some(r.le(pos + 1, 8).unwrap_or(0))),
+                    _ => (0, None),
+                };
+                match ip {
+                    Some(ip) => {
+                        last_ip = ip;
+                        let _ = writeln!(w, "{} {:#018x}", name, ip);
+                    }
+                    None => {
+                        let _ = writeln!(w, "{} suppressed", name);
+                    }
+                }
+                1 + bytes
+            }
+
+            _ => 0,
+        };
+
+        if len == 0 {
+            // Unknown packet: skip to the next PSB
+            let _ = writeln!(w, "unknown packet {:#04x} at {}, resyncing", b, pos);
+            synced = false;
+            pos += 1;
+            continue;
+        }
+        if b != 0x00 {
+            packets += 1;
+        }
+        pos += len;
+    }
+    packets
+}
+
+/// Print the trace of a buffer as hex
+fn hexdump(r: &mut Reader, w: &mut StdoutWriter, len: u64) {
+    for line in (0..len).step_by(16) {
+        let _ = write!(w, "{:08x}:", line);
+        for pos in line..(line + 16).min(len) {
+            let _ = write!(w, " {:02x}", r.byte(pos).unwrap_or(0));
+        }
+        let _ = writeln!(w);
+    }
+}
+
+fn dump_buffer(w: &mut StdoutWriter, info: &BufferInfo, vmo: Vmo) {
+    if info.cpu == itrace::CPU_THREAD {
+        let _ = write!(w, "== thread");
+    } else {
+        let _ = write!(w, "== cpu {}", info.cpu);
+    }
+    let _ = write!(w, ": {} KiB, offset {}", info.size / 1024, info.offset);
+    if info.flags & itrace::BUFFER_FULL != 0 {
+        let _ = write!(w, ", full");
+    }
+    let _ = writeln!(w);
+
+    if cfg!(target_arch = "x86_64") {
+        // Intel PT wraps, so decode from the write offset around to it
+        let mut reader = Reader::new(vmo, info.size, info.offset);
+        let packets = decode_pt(&mut reader, w);
+        let _ = writeln!(w, "{} packets", packets);
+    } else {
+        // ETE fills the buffer from its start
+        let mut reader = Reader::new(vmo, info.size, 0);
+        hexdump(&mut reader, w, info.offset);
+        let _ = writeln!(w, "{} bytes of ETE trace, decode with OpenCSD", info.offset);
+    }
+}
+
+fn dump(root: &Handle, w: &mut StdoutWriter, index: Option<u32>) -> Result<()> {
+    if let Some(index) = index {
+        let (info, vmo) = itrace::buffer(root, index)?;
+        dump_buffer(w, &info, vmo);
+        return Ok(());
+    }
+
+    // Every buffer, until the index runs past the last one
+    let mut index = 0;
+    loop {
+        match itrace::buffer(root, index) {
+            Ok((info, vmo)) => dump_buffer(w, &info, vmo),
+            Err(e) if index == 0 => return Err(e),
+            Err(_) => return Ok(()),
+        }
+        index += 1;
+    }
+}
+
+fn run(root: &Handle, argc: isize, argv: *const *const u8, w: &mut StdoutWriter) -> Option<Result<()>> {
+    let command = unsafe { arg(argv, 1) }?;
+    Some(match command {
+        "start" => start(root, &parse(argc, argv)?),
+        "stop" if argc == 2 => itrace::stop(root),
+        "dump" if argc == 2 => dump(root, w, None),
+        "dump" if argc == 3 => dump(root, w, Some(unsafe { arg(argv, 2) }?.parse().ok()?)),
+        "free" if argc == 2 => itrace::free(root),
+        _ => return None,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "itrace: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&root, argc.max(0) as isize, argv, &mut writer) {
+        Some(Ok(())) => 0,
+        Some(Err(e)) if e.status() == Status::NotSupported => {
+            let _ = writeln!(writer, "itrace: this CPU can't trace");
+            1
+        }
+        Some(Err(e)) if e.status() == Status::BadState => {
+            let _ = writeln!(writer, "itrace: trace is in use, or not stopped");
+            1
+        }
+        Some(Err(e)) => {
+            let _ = writeln!(writer, "itrace: {:?}", e);
+            1
+        }
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
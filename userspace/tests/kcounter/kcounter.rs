@@ -0,0 +1,188 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! kcounter - Inspect Kernel Counters
+//!
+//! Prints the kernel counters whose names start with any of the given
+//! prefixes, or all of them. With `-w`, keeps polling every `-i` seconds
+//! (default 1) and prints the counters that changed, with their change.
+//! With `-l`, prints only the names and types.
+//!
+//! Usage: `kcounter [-l] [-w] [-i seconds] [prefix...]`
+
+#![no_std]
+#![no_main]
+
+extern crate libsys;
+extern crate rt;
+
+use core::fmt::Write;
+
+use libsys::kcounter::{self, Record, KIND_MAX};
+use libsys::*;
+
+/// Most counters shown
+const MAX_COUNTERS: usize = 256;
+
+/// Most name prefixes on the command line
+const MAX_PREFIXES: usize = 16;
+
+const USAGE: &str = "usage: kcounter [-l] [-w] [-i seconds] [prefix...]";
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsed command line
+struct Options {
+    list: bool,
+    watch: bool,
+    interval: u64,
+    prefixes: [&'static str; MAX_PREFIXES],
+    prefix_count: usize,
+}
+
+impl Options {
+    fn parse(argc: i32, argv: *const *const u8) -> Option<Self> {
+        let mut opts = Self {
+            list: false,
+            watch: false,
+            interval: 1,
+            prefixes: [""; MAX_PREFIXES],
+            prefix_count: 0,
+        };
+
+        let mut args = (1..argc.max(0) as isize).filter_map(|i| unsafe { arg(argv, i) });
+        while let Some(arg) = args.next() {
+            match arg {
+                "-l" => opts.list = true,
+                "-w" => opts.watch = true,
+                "-i" => opts.interval = args.next()?.parse().ok().filter(|&s| s > 0)?,
+                _ if arg.starts_with('-') => return None,
+                _ if opts.prefix_count < MAX_PREFIXES => {
+                    opts.prefixes[opts.prefix_count] = arg;
+                    opts.prefix_count += 1;
+                }
+                _ => return None,
+            }
+        }
+        Some(opts)
+    }
+
+    fn selects(&self, name: &str) -> bool {
+        let prefixes = &self.prefixes[..self.prefix_count];
+        prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p))
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Snapshot buffers; too big for the initial stack
+static mut CURRENT: [Record; MAX_COUNTERS] = [Record::EMPTY; MAX_COUNTERS];
+static mut PREVIOUS: [Record; MAX_COUNTERS] = [Record::EMPTY; MAX_COUNTERS];
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let opts = match Options::parse(argc, argv) {
+        Some(opts) => opts,
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            return 1;
+        }
+    };
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "kcounter: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    let (current, previous) = unsafe {
+        (&mut *core::ptr::addr_of_mut!(CURRENT), &mut *core::ptr::addr_of_mut!(PREVIOUS))
+    };
+
+    let count = match kcounter::read(&root, current) {
+        Ok((actual, avail)) => {
+            if avail > actual {
+                let _ = writeln!(writer, "kcounter: showing {} of {} counters", actual, avail);
+            }
+            actual
+        }
+        Err(e) => {
+            let _ = writeln!(writer, "kcounter: read failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    for record in current[..count].iter().filter(|r| opts.selects(r.name())) {
+        let kind = if record.kind == KIND_MAX { "max" } else { "sum" };
+        if opts.list {
+            let _ = writeln!(writer, "{:<48} {}", record.name(), kind);
+        } else {
+            let _ = writeln!(writer, "{:<48} {:>16} {}", record.name(), record.value, kind);
+        }
+    }
+
+    if !opts.watch || opts.list {
+        return 0;
+    }
+
+    // Counters never move or disappear, so snapshots line up by index
+    loop {
+        previous[..count].copy_from_slice(&current[..count]);
+        rt::timer::sleep(opts.interval * 1_000_000_000);
+
+        if let Err(e) = kcounter::read(&root, &mut current[..count]) {
+            let _ = writeln!(writer, "kcounter: read failed: {:?}", e);
+            return 1;
+        }
+
+        let _ = writeln!(writer, "---");
+        for (now, before) in current[..count].iter().zip(previous[..count].iter()) {
+            if now.value != before.value && opts.selects(now.name()) {
+                let _ = writeln!(
+                    writer,
+                    "{:<48} {:>16} {:>+12}",
+                    now.name(),
+                    now.value,
+                    now.value.wrapping_sub(before.value)
+                );
+            }
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
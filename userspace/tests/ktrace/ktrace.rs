@@ -0,0 +1,284 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ktrace - Control and Extract the Kernel Trace
+//!
+//! `start` begins tracing the named groups (default all), `stop` ends it
+//! and `rewind` discards what was recorded. `dump` stops tracing and
+//! writes the trace to stdout as Chrome trace-event JSON, which both
+//! `chrome://tracing` and the Perfetto UI open directly, or as one line
+//! per record with `-f text`.
+//!
+//! Usage: `ktrace start [group...] | stop | rewind | dump [-f json|text]`
+//!
+//! Groups: `meta`, `lifecycle`, `sched`, `syscall`, `irq`, `ipc`,
+//! `probe`, `vm`, `all`
+
+#![no_std]
+#![no_main]
+
+extern crate libsys;
+
+use core::fmt::Write;
+
+use libsys::ktrace::{self, Record};
+use libsys::*;
+
+const USAGE: &str = "usage: ktrace start [group...] | stop | rewind | dump [-f json|text]";
+
+/// Group names accepted by `start`
+const GROUPS: [(&str, u32); 9] = [
+    ("meta", ktrace::GRP_META),
+    ("lifecycle", ktrace::GRP_LIFECYCLE),
+    ("sched", ktrace::GRP_SCHEDULER),
+    ("syscall", ktrace::GRP_SYSCALL),
+    ("irq", ktrace::GRP_IRQ),
+    ("ipc", ktrace::GRP_IPC),
+    ("probe", ktrace::GRP_PROBE),
+    ("vm", ktrace::GRP_VM),
+    ("all", ktrace::GRP_ALL),
+];
+
+/// Most probe names kept for labelling probe events
+const MAX_PROBES: usize = 64;
+
+/// Records read per syscall
+const CHUNK: usize = 256;
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Read buffer; too big for the initial stack
+static mut RECORDS: [Record; CHUNK] = [Record::EMPTY; CHUNK];
+
+/// Call `f` on every record in the trace
+fn for_each_record(root: &Handle, mut f: impl FnMut(&Record)) -> Result<()> {
+    let records = unsafe { &mut *core::ptr::addr_of_mut!(RECORDS) };
+    let mut index = 0;
+    loop {
+        let n = ktrace::read(root, index, records)?;
+        if n == 0 {
+            return Ok(());
+        }
+        records[..n].iter().filter(|r| r.tag != 0).for_each(&mut f);
+        index += n;
+    }
+}
+
+/// Probe names gathered from the trace's name records
+struct Probes {
+    names: [(u16, [u8; 16]); MAX_PROBES],
+    count: usize,
+}
+
+impl Probes {
+    fn collect(root: &Handle) -> Result<Self> {
+        let mut probes = Self { names: [(0, [0; 16]); MAX_PROBES], count: 0 };
+        for_each_record(root, |r| {
+            if r.tag == ktrace::TAG_PROBE_NAME && probes.name(r.id).is_none() && probes.count < MAX_PROBES {
+                let mut name = [0u8; 16];
+                name[..8].copy_from_slice(&r.args[0].to_le_bytes());
+                name[8..].copy_from_slice(&r.args[1].to_le_bytes());
+                probes.names[probes.count] = (r.id, name);
+                probes.count += 1;
+            }
+        })?;
+        Ok(probes)
+    }
+
+    fn name(&self, id: u16) -> Option<&str> {
+        let (_, name) = self.names[..self.count].iter().find(|(probe, _)| *probe == id)?;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        core::str::from_utf8(&name[..len]).ok()
+    }
+}
+
+/// Timestamp in microseconds, as Chrome trace events expect
+struct Micros(u64);
+
+impl core::fmt::Display for Micros {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+/// Write the trace as a Chrome trace-event JSON array
+///
+/// Each CPU becomes a thread of a single "kernel" process. Syscalls and
+/// interrupts are duration events; everything else is an instant event.
+fn dump_json(root: &Handle, probes: &Probes, w: &mut StdoutWriter) -> Result<()> {
+    let mut seen = [0u64; 4];
+
+    let _ = writeln!(w, "[");
+    let _ = write!(w, r#"{{"name":"process_name","ph":"M","pid":0,"args":{{"name":"kernel"}}}}"#);
+
+    for_each_record(root, |r| {
+        let cpu = r.cpu as usize;
+        if cpu < 256 && seen[cpu / 64] & (1 << (cpu % 64)) == 0 {
+            seen[cpu / 64] |= 1 << (cpu % 64);
+            let _ = write!(
+                w,
+                ",\n{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":\"cpu {}\"}}}}",
+                cpu, cpu
+            );
+        }
+
+        let ts = Micros(r.ts);
+        let head = |w: &mut StdoutWriter, cat: &str, ph: &str| {
+            let _ = write!(w, ",\n{{\"cat\":\"{}\",\"ph\":\"{}\",\"pid\":0,\"tid\":{},\"ts\":{}", cat, ph, cpu, ts);
+        };
+
+        match r.tag {
+            ktrace::TAG_SYSCALL_ENTER => {
+                head(w, "syscall", "B");
+                let _ = write!(w, ",\"name\":\"syscall {:#x}\",\"args\":{{\"arg0\":\"{:#x}\",\"arg1\":\"{:#x}\"}}}}",
+                    r.id, r.args[0], r.args[1]);
+            }
+            ktrace::TAG_SYSCALL_EXIT => {
+                head(w, "syscall", "E");
+                let _ = write!(w, ",\"name\":\"syscall {:#x}\",\"args\":{{\"ret\":{}}}}}", r.id, r.args[0] as i64);
+            }
+            ktrace::TAG_IRQ_ENTER | ktrace::TAG_IRQ_EXIT => {
+                head(w, "irq", if r.tag == ktrace::TAG_IRQ_ENTER { "B" } else { "E" });
+                let _ = write!(w, ",\"name\":\"irq {}\"}}", r.id);
+            }
+            ktrace::TAG_CONTEXT_SWITCH => {
+                head(w, "sched", "i");
+                let _ = write!(w, ",\"s\":\"t\",\"name\":\"context switch\",\"args\":{{\"from\":{},\"to\":{}}}}}",
+                    r.args[0], r.args[1]);
+            }
+            ktrace::TAG_PAGE_FAULT | ktrace::TAG_VMAR_MAP | ktrace::TAG_VMAR_UNMAP => {
+                let (name, second) = match r.tag {
+                    ktrace::TAG_PAGE_FAULT => ("page fault", "flags"),
+                    ktrace::TAG_VMAR_MAP => ("vmar map", "len"),
+                    _ => ("vmar unmap", "len"),
+                };
+                head(w, "vm", "i");
+                let _ = write!(w, ",\"s\":\"t\",\"name\":\"{}\",\"args\":{{\"addr\":\"{:#x}\",\"{}\":\"{:#x}\"}}}}",
+                    name, r.args[0], second, r.args[1]);
+            }
+            ktrace::TAG_PROBE_NAME => {}
+            _ if r.group() == ktrace::GRP_PROBE => {
+                head(w, "probe", "i");
+                let _ = match probes.name(r.id) {
+                    Some(name) => write!(w, ",\"s\":\"t\",\"name\":\"{}\"", name),
+                    None => write!(w, ",\"s\":\"t\",\"name\":\"probe {}\"", r.id),
+                };
+                let _ = write!(w, ",\"args\":{{\"arg0\":{},\"arg1\":{}}}}}", r.args[0], r.args[1]);
+            }
+            _ => {
+                head(w, "unknown", "i");
+                let _ = write!(w, ",\"s\":\"t\",\"name\":\"tag {:#x}\"}}", r.tag);
+            }
+        }
+    })?;
+
+    let _ = writeln!(w, "\n]");
+    Ok(())
+}
+
+/// Write the trace one record per line
+fn dump_text(root: &Handle, w: &mut StdoutWriter) -> Result<()> {
+    for_each_record(root, |r| {
+        let _ = writeln!(
+            w,
+            "{:>14} cpu{:<3} tag {:#08x} id {:#06x} {:#018x} {:#018x}",
+            Micros(r.ts), r.cpu, r.tag, r.id, r.args[0], r.args[1]
+        );
+    })
+}
+
+fn run(root: &Handle, argc: isize, argv: *const *const u8, w: &mut StdoutWriter) -> Option<Result<()>> {
+    if argc < 2 {
+        return None;
+    }
+    let command = unsafe { arg(argv, 1) }?;
+    let mut args = (2..argc).filter_map(|i| unsafe { arg(argv, i) });
+
+    Some(match command {
+        "start" => {
+            let mut groups = 0;
+            for name in args {
+                groups |= GROUPS.iter().find(|(g, _)| *g == name)?.1;
+            }
+            ktrace::start(root, if groups == 0 { ktrace::GRP_ALL } else { groups })
+        }
+        "stop" => ktrace::stop(root),
+        "rewind" => ktrace::rewind(root),
+        "dump" => {
+            let json = match (args.next(), args.next()) {
+                (None, _) => true,
+                (Some("-f"), Some("json")) => true,
+                (Some("-f"), Some("text")) => false,
+                _ => return None,
+            };
+            ktrace::stop(root).and_then(|_| {
+                if json {
+                    dump_json(root, &Probes::collect(root)?, w)
+                } else {
+                    dump_text(root, w)
+                }
+            })
+        }
+        _ => return None,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "ktrace: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&root, argc.max(0) as isize, argv, &mut writer) {
+        Some(Ok(())) => 0,
+        Some(Err(e)) => {
+            let _ = writeln!(writer, "ktrace: {:?}", e);
+            1
+        }
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
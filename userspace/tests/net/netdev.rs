@@ -0,0 +1,86 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Shared setup for the network test tools
+//!
+//! Like the block tools, each tool binds the first virtio-net device
+//! itself through an in-process driver host and runs its own stack on
+//! the ethernet protocol. Run the tools instead of `devhost`.
+
+// Not every tool uses every helper
+#![allow(dead_code)]
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use libddk::*;
+use libsys::*;
+
+/// Simple stdout writer
+pub struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Drivers the tools can bind
+static DRIVERS: [DriverEntry; 1] = [virtio::net::DRIVER];
+
+/// Command-line arguments after the program name
+pub fn args(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    let mut args = Vec::new();
+    for i in 1..argc.max(0) as isize {
+        unsafe {
+            let arg = *argv.offset(i);
+            if arg.is_null() {
+                break;
+            }
+            let mut len = 0;
+            while *arg.add(len) != 0 {
+                len += 1;
+            }
+            if let Ok(s) = core::str::from_utf8(core::slice::from_raw_parts(arg, len)) {
+                args.push(s);
+            }
+        }
+    }
+    args
+}
+
+/// Bind the first virtio-net device on the PCI bus and connect to it
+///
+/// The client polls the in-process host whenever its receive queue is
+/// empty.
+pub fn open_link() -> Result<EthernetClient> {
+    let root = startup::root_resource()?;
+
+    let mut index = 0;
+    let local = loop {
+        let device = PciDevice::get_nth(&root, index)?;
+        index += 1;
+        let desc = device.info().descriptor();
+        if (virtio::net::DRIVER.matches)(&desc) {
+            break LocalDevice::bind(&DRIVERS, desc, device.into_handle())?;
+        }
+    };
+    let local = Rc::new(RefCell::new(local));
+
+    let (body, handles) = local.borrow_mut().open(0)?;
+    let mut client = EthernetClient::new(&body, handles)?;
+    client.set_idle(Box::new(move || {
+        let _ = local.borrow_mut().host_mut().poll();
+    }));
+    Ok(client)
+}
@@ -0,0 +1,133 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ping - Send ICMP Echo Requests
+//!
+//! Pings `addr` (default 10.0.2.2, the QEMU user-networking gateway)
+//! `count` times and reports each reply. There is no clock to time
+//! replies against, so a request is given up after a fixed number of
+//! stack polls.
+//!
+//! Usage: `ping [addr] [count]`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libsys;
+extern crate netstack;
+extern crate virtio;
+
+mod netdev;
+
+use core::fmt::Write;
+use core::net::Ipv4Addr;
+
+use netstack::{Config, Stack};
+
+use netdev::StdoutWriter;
+
+/// Echo identifier used for every request
+const PING_ID: u16 = 0x7278;
+
+/// Payload size, as in the classic `ping` default
+const PAYLOAD_SIZE: usize = 56;
+
+/// Polls to wait for each reply
+const REPLY_POLLS: usize = 200_000;
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+    let args = netdev::args(argc, argv);
+
+    let dst = match args.first().map(|s| s.parse::<Ipv4Addr>()) {
+        None => Config::qemu_user().gateway,
+        Some(Ok(addr)) => addr,
+        Some(Err(_)) => {
+            let _ = writeln!(writer, "usage: ping [addr] [count]");
+            return 1;
+        }
+    };
+    let count = match args.get(1).map(|s| s.parse::<u16>()) {
+        None => 4,
+        Some(Ok(count)) => count,
+        Some(Err(_)) => {
+            let _ = writeln!(writer, "usage: ping [addr] [count]");
+            return 1;
+        }
+    };
+
+    let link = match netdev::open_link() {
+        Ok(link) => link,
+        Err(e) => {
+            let _ = writeln!(writer, "ping: no network device: {:?}", e);
+            return 1;
+        }
+    };
+    let mut stack = Stack::new(link, Config::qemu_user());
+
+    let mut payload = [0u8; PAYLOAD_SIZE];
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    let _ = writeln!(writer, "PING {} from {}: {} data bytes", dst, stack.config().ip, PAYLOAD_SIZE);
+    let mut received = 0;
+    for seq in 0..count {
+        if let Err(e) = stack.ping(dst, PING_ID, seq, &payload) {
+            let _ = writeln!(writer, "ping: send failed: {:?}", e);
+            return 1;
+        }
+
+        let mut reply = None;
+        for _ in 0..REPLY_POLLS {
+            if let Err(e) = stack.poll() {
+                let _ = writeln!(writer, "ping: poll failed: {:?}", e);
+                return 1;
+            }
+            // Drop late replies to earlier requests
+            while let Some(r) = stack.take_echo_reply(PING_ID) {
+                if r.seq == seq {
+                    reply = Some(r);
+                }
+            }
+            if reply.is_some() {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+
+        match reply {
+            Some(r) => {
+                let _ = writeln!(writer, "{} bytes from {}: icmp_seq={}", r.len + 8, r.src, r.seq);
+                received += 1;
+            }
+            None => {
+                let _ = writeln!(writer, "request timeout for icmp_seq={}", seq);
+            }
+        }
+    }
+
+    let _ = writeln!(
+        writer,
+        "--- {} ping statistics ---\n{} packets transmitted, {} received",
+        dst, count, received
+    );
+    if received == 0 {
+        1
+    } else {
+        0
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
@@ -0,0 +1,113 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! tcpecho - TCP Echo Server
+//!
+//! Accepts connections on `port` (default 7) and sends everything each
+//! client writes back to it, closing when the client does. Under QEMU
+//! user networking, forward a host port to reach it:
+//!
+//! ```text
+//! -netdev user,id=net0,hostfwd=tcp::5555-:7
+//! nc localhost 5555
+//! ```
+//!
+//! Usage: `tcpecho [port]`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libsys;
+extern crate netstack;
+extern crate virtio;
+
+mod netdev;
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use netstack::{Config, Stack, TcpStream};
+
+use netdev::StdoutWriter;
+
+/// Connections waiting to be accepted
+const BACKLOG: usize = 4;
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+    let args = netdev::args(argc, argv);
+
+    let port = match args.first().map(|s| s.parse::<u16>()) {
+        None => 7,
+        Some(Ok(port)) if port != 0 => port,
+        Some(_) => {
+            let _ = writeln!(writer, "usage: tcpecho [port]");
+            return 1;
+        }
+    };
+
+    let link = match netdev::open_link() {
+        Ok(link) => link,
+        Err(e) => {
+            let _ = writeln!(writer, "tcpecho: no network device: {:?}", e);
+            return 1;
+        }
+    };
+    let mut stack = Stack::new(link, Config::qemu_user());
+    let listener = match stack.tcp_listen(port, BACKLOG) {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = writeln!(writer, "tcpecho: listen failed: {:?}", e);
+            return 1;
+        }
+    };
+    let _ = writeln!(writer, "tcpecho: listening on {}:{}", stack.config().ip, port);
+
+    let mut clients: Vec<TcpStream> = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        if let Err(e) = stack.poll() {
+            let _ = writeln!(writer, "tcpecho: poll failed: {:?}", e);
+            return 1;
+        }
+
+        while let Ok(Some(stream)) = listener.accept() {
+            if let Some(peer) = stream.peer_addr() {
+                let _ = writeln!(writer, "tcpecho: connection from {}", peer);
+            }
+            clients.push(stream);
+        }
+
+        let mut i = 0;
+        while i < clients.len() {
+            let done = match clients[i].recv(&mut buf) {
+                Ok(Some(0)) => true,
+                Ok(Some(len)) => clients[i].send(&buf[..len]).is_err(),
+                Ok(None) => false,
+                Err(e) => {
+                    let _ = writeln!(writer, "tcpecho: connection failed: {:?}", e);
+                    true
+                }
+            };
+            if done {
+                let _ = clients.swap_remove(i).close();
+            } else {
+                i += 1;
+            }
+        }
+        core::hint::spin_loop();
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
@@ -0,0 +1,98 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! udpecho - UDP Echo Server
+//!
+//! Binds `port` (default 7) and sends every datagram back to its
+//! sender. Under QEMU user networking, forward a host port to reach it:
+//!
+//! ```text
+//! -netdev user,id=net0,hostfwd=udp::5555-:7
+//! echo hello | nc -u -w1 localhost 5555
+//! ```
+//!
+//! Usage: `udpecho [port]`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libddk;
+extern crate libsys;
+extern crate netstack;
+extern crate virtio;
+
+mod netdev;
+
+use core::fmt::Write;
+
+use netstack::socket::MAX_DATAGRAM;
+use netstack::{Config, Stack};
+
+use netdev::StdoutWriter;
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+    let args = netdev::args(argc, argv);
+
+    let port = match args.first().map(|s| s.parse::<u16>()) {
+        None => 7,
+        Some(Ok(port)) if port != 0 => port,
+        Some(_) => {
+            let _ = writeln!(writer, "usage: udpecho [port]");
+            return 1;
+        }
+    };
+
+    let link = match netdev::open_link() {
+        Ok(link) => link,
+        Err(e) => {
+            let _ = writeln!(writer, "udpecho: no network device: {:?}", e);
+            return 1;
+        }
+    };
+    let mut stack = Stack::new(link, Config::qemu_user());
+    let socket = match stack.udp_bind(port) {
+        Ok(socket) => socket,
+        Err(e) => {
+            let _ = writeln!(writer, "udpecho: bind failed: {:?}", e);
+            return 1;
+        }
+    };
+    let _ = writeln!(writer, "udpecho: listening on {}:{}", stack.config().ip, port);
+
+    let mut buf = [0u8; MAX_DATAGRAM];
+    loop {
+        if let Err(e) = stack.poll() {
+            let _ = writeln!(writer, "udpecho: poll failed: {:?}", e);
+            return 1;
+        }
+        loop {
+            match socket.recvfrom(&mut buf) {
+                Ok(Some((len, from))) => {
+                    let _ = writeln!(writer, "udpecho: {} bytes from {}", len, from);
+                    if let Err(e) = socket.sendto(from, &buf[..len]) {
+                        let _ = writeln!(writer, "udpecho: send failed: {:?}", e);
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = writeln!(writer, "udpecho: receive failed: {:?}", e);
+                    return 1;
+                }
+            }
+        }
+        core::hint::spin_loop();
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
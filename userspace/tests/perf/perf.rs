@@ -0,0 +1,293 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! perf - Count Hardware Events
+//!
+//! `info` shows what the performance counters can count. `stat` counts
+//! events for a while, on every CPU or, with `-t`, for one thread, and
+//! prints the totals. `record` samples where one event happens, into the
+//! sampling profiler's buffers, for `prof dump` to fold.
+//!
+//! Events are `cycles`, `instructions`, `cache-misses`, `branch-misses`
+//! or `r<hex>` for a raw hardware event. `stat` defaults to cycles and
+//! instructions, `record` to cycles every 100000 events. `-u` and `-k`
+//! count only user or only kernel code.
+//!
+//! Usage:
+//!
+//! - `perf info`
+//! - `perf stat [-e <ev,...>] [-t <tid>] [-u|-k] <ms>`
+//! - `perf record [-e <ev>] [-p <period>] [-u|-k] <ms>`
+
+#![no_std]
+#![no_main]
+
+extern crate libsys;
+extern crate rt;
+
+use core::fmt::Write;
+
+use libsys::perfmon::{self, Config, Counts, MAX_COUNTERS};
+use libsys::*;
+
+const USAGE: &str = "usage: perf info | stat [-e <ev,...>] [-t <tid>] [-u|-k] <ms> | \
+                     record [-e <ev>] [-p <period>] [-u|-k] <ms>";
+
+/// Events of counter 0 between samples for `record`
+const DEFAULT_PERIOD: u64 = 100_000;
+
+/// Most CPUs `stat` reports
+const MAX_CPUS: usize = 64;
+
+/// Generic event names, by event number
+const EVENTS: &[(&str, u32)] = &[
+    ("cycles", perfmon::EVENT_CYCLES),
+    ("instructions", perfmon::EVENT_INSTRUCTIONS),
+    ("cache-misses", perfmon::EVENT_CACHE_MISSES),
+    ("branch-misses", perfmon::EVENT_BRANCH_MISSES),
+];
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Event number for an event name
+fn parse_event(name: &str) -> Option<u32> {
+    if let Some(hex) = name.strip_prefix('r') {
+        let raw = u32::from_str_radix(hex, 16).ok()?;
+        return (raw <= 0xffff).then_some(perfmon::EVENT_RAW | raw);
+    }
+    EVENTS.iter().find(|(n, _)| *n == name).map(|&(_, event)| event)
+}
+
+/// Write the name of an event, right-aligned in a column if `column`
+fn write_event(w: &mut StdoutWriter, event: u32, column: bool) {
+    let width = if column { 16 } else { 0 };
+    match EVENTS.iter().find(|&&(_, e)| e == event) {
+        Some((name, _)) => {
+            let _ = write!(w, "{:>width$}", name);
+        }
+        None => {
+            let _ = write!(w, "{:>pad$}r{:04x}", "", event & 0xffff, pad = width.saturating_sub(5));
+        }
+    }
+}
+
+/// Options shared by `stat` and `record`
+struct Options {
+    config: Config,
+    ms: u64,
+}
+
+/// Parse the options from argument 2 on
+fn parse(argc: isize, argv: *const *const u8, record: bool) -> Option<Options> {
+    let mut config = Config::default();
+    let mut ms = None;
+    let mut i = 2;
+    while i < argc {
+        let a = unsafe { arg(argv, i) }?;
+        let value = if i + 1 < argc { unsafe { arg(argv, i + 1) } } else { None };
+        match a {
+            "-e" => {
+                for name in value?.split(',') {
+                    if config.count as usize == MAX_COUNTERS || (record && config.count == 1) {
+                        return None;
+                    }
+                    config.events[config.count as usize] = parse_event(name)?;
+                    config.count += 1;
+                }
+                i += 1;
+            }
+            "-t" if !record => {
+                config.mode = perfmon::MODE_THREAD;
+                config.thread = value?.parse().ok()?;
+                i += 1;
+            }
+            "-p" if record => {
+                config.sample_period = value?.parse().ok()?;
+                i += 1;
+            }
+            "-u" => config.flags = perfmon::FLAG_USER,
+            "-k" => config.flags = perfmon::FLAG_KERNEL,
+            _ if ms.is_none() => ms = Some(a.parse().ok()?),
+            _ => return None,
+        }
+        i += 1;
+    }
+
+    if config.count == 0 {
+        config.events[0] = perfmon::EVENT_CYCLES;
+        config.count = 1;
+        if !record {
+            config.events[1] = perfmon::EVENT_INSTRUCTIONS;
+            config.count = 2;
+        }
+    }
+    if record && config.sample_period == 0 {
+        config.sample_period = DEFAULT_PERIOD;
+    }
+    Some(Options { config, ms: ms? })
+}
+
+fn info(root: &Handle, w: &mut StdoutWriter) -> Result<()> {
+    let props = perfmon::properties(root)?;
+    if props.counters == 0 {
+        let _ = writeln!(w, "perf: no performance counters");
+        return Ok(());
+    }
+    let _ = writeln!(w, "{} counters of {} bits", props.counters, props.counter_bits);
+    for &(name, event) in EVENTS {
+        let supported = props.events & (1 << event) != 0;
+        let _ = writeln!(w, "  {:<14} {}", name, if supported { "yes" } else { "no" });
+    }
+    Ok(())
+}
+
+/// Count for `ms` milliseconds
+fn count(root: &Handle, config: &Config, ms: u64) -> Result<()> {
+    perfmon::start(root, config)?;
+    rt::timer::sleep(ms * 1_000_000);
+    perfmon::stop(root)
+}
+
+fn stat(root: &Handle, w: &mut StdoutWriter, options: &Options) -> Result<()> {
+    let config = &options.config;
+    count(root, config, options.ms)?;
+
+    let mut counts = [Counts::default(); MAX_CPUS];
+    let n = perfmon::read(root, &mut counts)?;
+
+    let _ = write!(w, "{:>8}", "cpu");
+    for &event in &config.events[..config.count as usize] {
+        let _ = write!(w, " ");
+        write_event(w, event, true);
+    }
+    let _ = writeln!(w);
+
+    let mut totals = [0u64; MAX_COUNTERS];
+    for counts in &counts[..n] {
+        if counts.cpu == perfmon::CPU_THREAD {
+            let _ = write!(w, "{:>8}", "thread");
+        } else {
+            let _ = write!(w, "{:>8}", counts.cpu);
+        }
+        for (total, &value) in totals.iter_mut().zip(counts.values()) {
+            let _ = write!(w, " {:>16}", value);
+            *total += value;
+        }
+        let _ = writeln!(w);
+    }
+    if n > 1 {
+        let _ = write!(w, "{:>8}", "total");
+        for total in &totals[..config.count as usize] {
+            let _ = write!(w, " {:>16}", total);
+        }
+        let _ = writeln!(w);
+    }
+
+    // Instructions per cycle, when both were counted
+    let events = &config.events[..config.count as usize];
+    let index = |event| events.iter().position(|&e| e == event);
+    if let (Some(c), Some(i)) = (index(perfmon::EVENT_CYCLES), index(perfmon::EVENT_INSTRUCTIONS)) {
+        if totals[c] != 0 {
+            let ipc = totals[i] * 100 / totals[c];
+            let _ = writeln!(w, "{}.{:02} instructions per cycle", ipc / 100, ipc % 100);
+        }
+    }
+    Ok(())
+}
+
+fn record(root: &Handle, w: &mut StdoutWriter, options: &Options) -> Result<()> {
+    profiler::rewind(root)?;
+    count(root, &options.config, options.ms)?;
+
+    let mut samples = [profiler::Sample::EMPTY; 16];
+    let mut total = 0;
+    loop {
+        let n = profiler::read(root, total, &mut samples)?;
+        if n == 0 {
+            break;
+        }
+        total += n;
+    }
+    let _ = write!(w, "perf: {} samples of ", total);
+    write_event(w, options.config.events[0], false);
+    let _ = writeln!(w, " every {} events; fold them with `prof dump`", options.config.sample_period);
+    Ok(())
+}
+
+fn run(root: &Handle, argc: isize, argv: *const *const u8, w: &mut StdoutWriter) -> Option<Result<()>> {
+    let command = unsafe { arg(argv, 1) }?;
+    Some(match command {
+        "info" if argc == 2 => info(root, w),
+        "stat" => stat(root, w, &parse(argc, argv, false)?),
+        "record" => record(root, w, &parse(argc, argv, true)?),
+        _ => return None,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "perf: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&root, argc.max(0) as isize, argv, &mut writer) {
+        Some(Ok(())) => 0,
+        Some(Err(e)) if e.status() == Status::NotSupported => {
+            let _ = writeln!(writer, "perf: event not supported, see `perf info`");
+            1
+        }
+        Some(Err(e)) if e.status() == Status::BadState => {
+            let _ = writeln!(writer, "perf: counters already in use");
+            1
+        }
+        Some(Err(e)) => {
+            let _ = writeln!(writer, "perf: {:?}", e);
+            1
+        }
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}
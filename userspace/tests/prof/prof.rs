@@ -0,0 +1,170 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! prof - Control the Sampling Profiler and Fold Its Samples
+//!
+//! `start` begins sampling, every `period` microseconds if given, `stop`
+//! ends it and `rewind` discards the samples. `dump` stops sampling and
+//! writes one line per distinct stack in the folded format that
+//! `flamegraph.pl` and speedscope read: frames outermost first, separated
+//! by `;`, then the number of samples.
+//!
+//! Stacks are rooted at their process (`pid <n>`), with `-t` also at their
+//! thread. Samples of kernel code are marked with a `kernel` frame, and
+//! samples outside any process are rooted at `kernel` alone. Frames are
+//! raw addresses; symbolize them against the binaries on the host.
+//!
+//! Usage: `prof start [period_us] | stop | rewind | dump [-t]`
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+extern crate libsys;
+extern crate rt;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use libsys::profiler::{self, Sample};
+use libsys::*;
+
+const USAGE: &str = "usage: prof start [period_us] | stop | rewind | dump [-t]";
+
+/// Samples read per syscall
+const CHUNK: usize = 128;
+
+/// Simple stdout writer
+struct StdoutWriter;
+
+impl core::fmt::Write for StdoutWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                syscall::syscall1(syscall::SyscallNumber::WriteStdio as u64, b as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line argument `i`
+unsafe fn arg(argv: *const *const u8, i: isize) -> Option<&'static str> {
+    let arg = *argv.offset(i);
+    if arg.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *arg.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(arg, len)).ok()
+}
+
+/// Where a stack is rooted
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Root {
+    pid: u64,
+    tid: Option<u64>,
+    kernel: bool,
+}
+
+/// Count the samples of every distinct stack
+fn fold(root: &Handle, threads: bool) -> Result<BTreeMap<(Root, Vec<u64>), u64>> {
+    let mut stacks = BTreeMap::new();
+    let mut samples = alloc::vec![Sample::EMPTY; CHUNK];
+    let mut index = 0;
+    loop {
+        let n = profiler::read(root, index, &mut samples)?;
+        if n == 0 {
+            return Ok(stacks);
+        }
+        for sample in samples[..n].iter().filter(|s| s.depth != 0) {
+            let key = Root {
+                pid: sample.pid,
+                tid: if threads { Some(sample.tid) } else { None },
+                kernel: !sample.is_user(),
+            };
+            let frames = sample.frames().iter().rev().copied().collect();
+            *stacks.entry((key, frames)).or_insert(0) += 1;
+        }
+        index += n;
+    }
+}
+
+/// Write the samples as folded stacks
+fn dump(root: &Handle, threads: bool, w: &mut StdoutWriter) -> Result<()> {
+    for ((key, frames), count) in fold(root, threads)? {
+        if key.pid == 0 {
+            let _ = write!(w, "kernel");
+        } else {
+            let _ = write!(w, "pid {}", key.pid);
+            if let Some(tid) = key.tid {
+                let _ = write!(w, ";tid {}", tid);
+            }
+            if key.kernel {
+                let _ = write!(w, ";kernel");
+            }
+        }
+        for pc in frames {
+            let _ = write!(w, ";{:#x}", pc);
+        }
+        let _ = writeln!(w, " {}", count);
+    }
+    Ok(())
+}
+
+fn run(root: &Handle, argc: isize, argv: *const *const u8, w: &mut StdoutWriter) -> Option<Result<()>> {
+    let command = unsafe { arg(argv, 1) }?;
+    let option = if argc > 2 { unsafe { arg(argv, 2) } } else { None };
+
+    Some(match (command, argc) {
+        ("start", 2) => profiler::start(root, 0),
+        ("start", 3) => profiler::start(root, option?.parse().ok()?),
+        ("stop", 2) => profiler::stop(root),
+        ("rewind", 2) => profiler::rewind(root),
+        ("dump", 2) => profiler::stop(root).and_then(|_| dump(root, false, w)),
+        ("dump", 3) if option == Some("-t") => profiler::stop(root).and_then(|_| dump(root, true, w)),
+        _ => return None,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let mut writer = StdoutWriter;
+
+    let root = match startup::root_resource() {
+        Ok(root) => root,
+        Err(e) => {
+            let _ = writeln!(writer, "prof: no root resource: {:?}", e);
+            return 1;
+        }
+    };
+
+    match run(&root, argc.max(0) as isize, argv, &mut writer) {
+        Some(Ok(())) => 0,
+        Some(Err(e)) if e.status() == Status::NotSupported => {
+            let _ = writeln!(writer, "prof: profiler disabled (profiler.bufsize=0)");
+            1
+        }
+        Some(Err(e)) => {
+            let _ = writeln!(writer, "prof: {:?}", e);
+            1
+        }
+        None => {
+            let _ = writeln!(writer, "{}", USAGE);
+            1
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut writer = StdoutWriter;
+    let _ = writeln!(writer, "PANIC: {:?}", info);
+    libsys::Process::exit(1)
+}